@@ -44,7 +44,7 @@ cat-warn-bzip2-missing = 警告: bzip2 解凍は利用できないため、そ
 cat-warn-xz-missing = 警告: XZ 解凍は利用できないため、そのまま読み込みます
 cat-warn-zstd-missing = 警告: zstd 解凍は利用できないため、そのまま読み込みます
 cat-help-advanced-title = 追加オプション:
-cat-help-advanced-options =       --progress           大きなファイルで進捗バーを表示\n      --parallel           複数ファイルを並列処理\n      --threads N          並列処理のスレッド数\n      --encoding ENC       特定のエンコーディングを強制 (utf-8, utf-16le など)\n      --binary             すべてのファイルをバイナリとして扱う\n      --text               すべてのファイルをテキストとして扱う\n      --skip-binary        バイナリファイルをスキップ\n      --format FMT         出力形式 (raw, hex, base64, json)\n      --color WHEN         出力の色付け (always, never, auto)\n      --statistics         処理統計を表示\n      --buffer-size N      I/O のバッファサイズ\n      --no-mmap            大きなファイルのメモリマップを無効化\n      --no-decompress      自動展開を無効化\n      --no-follow-symlinks シンボリックリンクを辿らない\n      --timeout N          ネットワークのタイムアウト秒数\n      --help               このヘルプを表示して終了\n      --version            バージョン情報を表示して終了
+cat-help-advanced-options =       --progress           大きなファイルで進捗バーを表示\n      --parallel           複数ファイルを並列処理\n      --threads N          並列処理のスレッド数\n      --encoding ENC       特定のエンコーディングを強制 (utf-8, utf-16le など)\n      --binary             すべてのファイルをバイナリとして扱う\n      --text               すべてのファイルをテキストとして扱う\n      --skip-binary        バイナリファイルをスキップ\n      --format FMT         出力形式 (raw, hex, base64, json)\n      --color WHEN         出力の色付け (always, never, auto)\n      --statistics         処理統計を表示\n      --buffer-size N      I/O のバッファサイズ\n      --no-mmap            大きなファイルのメモリマップを無効化\n  -z, --decompress         圧縮された標準入力も展開\n      --no-decompress      自動展開を無効化\n      --no-follow-symlinks シンボリックリンクを辿らない\n      --timeout N          ネットワークのタイムアウト秒数\n      --help               このヘルプを表示して終了\n      --version            バージョン情報を表示して終了
 cat-help-advanced-examples-title = 応用例:
 cat-help-advanced-example1 =   cat --parallel --progress *.log    ログを進捗表示付きで並列処理
 cat-help-advanced-example2 =   cat --format hex data.bin          バイナリを16進で出力
@@ -0,0 +1,233 @@
+//! `abbr` builtin - manage fish-style abbreviations
+//!
+//! Syntax:
+//!   abbr [-a] [--position command|anywhere] [--set-cursor[=MARKER]] NAME EXPANSION...
+//!   abbr -e NAME...        # erase abbreviations
+//!   abbr -r OLD NEW        # rename an abbreviation
+//!   abbr -q NAME...        # succeed if any NAME is an abbreviation
+//!   abbr -l                # list names
+//!   abbr [-s]              # print abbreviations as `abbr` commands
+//!
+//! The line editor expands an abbreviation in place when Space or Enter
+//! follows it, so the command can be checked and edited before it runs.
+//! With `--set-cursor` the cursor is left where MARKER (default `%`) was.
+//! The registry is `nxsh_ui::abbreviations::shared()` and lasts for the
+//! session; define abbreviations in `~/.nxshrc` to keep them.
+
+use crate::common::{shell_quote, usage_error};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_ui::abbreviations::{self, AbbrPosition, Abbreviation};
+
+/// The `abbr` builtin command implementation
+pub struct AbbrCommand;
+
+/// What an `abbr` invocation asks for
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Add,
+    Erase,
+    Rename,
+    Query,
+    List,
+    Show,
+}
+
+impl Builtin for AbbrCommand {
+    fn name(&self) -> &'static str {
+        "abbr"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Manage abbreviations"
+    }
+
+    fn description(&self) -> &'static str {
+        "Define words the line editor expands in place when Space or Enter follows them, \
+         or list, rename and erase them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "abbr [-a] [--position command|anywhere] [--set-cursor[=MARKER]] NAME EXPANSION... | \
+         abbr -e|-q NAME... | abbr -r OLD NEW | abbr [-l|-s]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Manage abbreviations. Use 'abbr -s' to print them."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut mode = None;
+        let mut position = AbbrPosition::Command;
+        let mut cursor_marker = None;
+        let mut rest = args;
+        while let Some(flag) = rest.first() {
+            let next = match flag.as_str() {
+                "-a" | "--add" => Some(Mode::Add),
+                "-e" | "--erase" => Some(Mode::Erase),
+                "-r" | "--rename" => Some(Mode::Rename),
+                "-q" | "--query" => Some(Mode::Query),
+                "-l" | "--list" => Some(Mode::List),
+                "-s" | "--show" => Some(Mode::Show),
+                "-p" | "--position" => {
+                    position = match rest.get(1).map(String::as_str) {
+                        Some("command") => AbbrPosition::Command,
+                        Some("anywhere") => AbbrPosition::Anywhere,
+                        Some(other) => {
+                            return Ok(usage_error(&format!("abbr: {other}: invalid position")))
+                        }
+                        None => return Ok(usage_error("abbr: --position: missing argument")),
+                    };
+                    rest = &rest[1..];
+                    None
+                }
+                "--set-cursor" => {
+                    cursor_marker = Some("%".to_string());
+                    None
+                }
+                f if f.starts_with("--set-cursor=") => {
+                    cursor_marker = Some(f["--set-cursor=".len()..].to_string());
+                    None
+                }
+                "--" => {
+                    rest = &rest[1..];
+                    break;
+                }
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Ok(usage_error(&format!("abbr: {f}: invalid option")));
+                }
+                _ => break,
+            };
+            if let Some(next) = next {
+                if mode.as_ref().is_some_and(|m| *m != next) {
+                    return Ok(usage_error(
+                        "abbr: only one of -a, -e, -r, -q, -l, -s may be given",
+                    ));
+                }
+                mode = Some(next);
+            }
+            rest = &rest[1..];
+        }
+        let mode = mode.unwrap_or(if rest.is_empty() {
+            Mode::Show
+        } else {
+            Mode::Add
+        });
+
+        let registry = abbreviations::shared();
+        match mode {
+            Mode::Show | Mode::List => {
+                let abbrs = registry.read().unwrap_or_else(|e| e.into_inner());
+                let mut out = String::new();
+                for abbr in abbrs.iter() {
+                    if mode == Mode::List {
+                        out.push_str(&abbr.name);
+                    } else {
+                        out.push_str(&show(abbr));
+                    }
+                    out.push('\n');
+                }
+                Ok(ExecutionResult::success(0).with_output(out.into_bytes()))
+            }
+            Mode::Query => {
+                let abbrs = registry.read().unwrap_or_else(|e| e.into_inner());
+                let found = rest.iter().any(|name| abbrs.get(name).is_some());
+                Ok(ExecutionResult::success(if found { 0 } else { 1 }))
+            }
+            Mode::Add => {
+                let [name, expansion @ ..] = rest else {
+                    return Ok(usage_error("abbr: usage: abbr -a NAME EXPANSION..."));
+                };
+                if expansion.is_empty() {
+                    return Ok(usage_error(&format!("abbr: {name}: missing expansion")));
+                }
+                if !valid_name(name) {
+                    return Ok(usage_error(&format!(
+                        "abbr: {name}: invalid abbreviation name"
+                    )));
+                }
+                let mut abbrs = registry.write().unwrap_or_else(|e| e.into_inner());
+                abbrs.add(Abbreviation {
+                    position,
+                    cursor_marker,
+                    ..Abbreviation::new(name.as_str(), expansion.join(" "))
+                });
+                Ok(ExecutionResult::success(0))
+            }
+            Mode::Erase => {
+                let mut abbrs = registry.write().unwrap_or_else(|e| e.into_inner());
+                let mut stderr = String::new();
+                for name in rest {
+                    if !abbrs.remove(name) {
+                        stderr.push_str(&format!("abbr: {name}: no such abbreviation\n"));
+                    }
+                }
+                if stderr.is_empty() {
+                    Ok(ExecutionResult::success(0))
+                } else {
+                    Ok(ExecutionResult::failure(1).with_error(stderr.into_bytes()))
+                }
+            }
+            Mode::Rename => {
+                let [from, to] = rest else {
+                    return Ok(usage_error("abbr: usage: abbr -r OLD NEW"));
+                };
+                if !valid_name(to) {
+                    return Ok(usage_error(&format!(
+                        "abbr: {to}: invalid abbreviation name"
+                    )));
+                }
+                let mut abbrs = registry.write().unwrap_or_else(|e| e.into_inner());
+                if abbrs.get(to).is_some() {
+                    return Ok(ExecutionResult::failure(1)
+                        .with_error(format!("abbr: {to}: already exists\n").into_bytes()));
+                }
+                if !abbrs.rename(from, to) {
+                    return Ok(ExecutionResult::failure(1)
+                        .with_error(format!("abbr: {from}: no such abbreviation\n").into_bytes()));
+                }
+                Ok(ExecutionResult::success(0))
+            }
+        }
+    }
+}
+
+impl AbbrCommand {
+    /// Create a new abbr command instance
+    pub fn new() -> Self {
+        AbbrCommand
+    }
+}
+
+impl Default for AbbrCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `abbr -s` line that recreates `abbr`
+fn show(abbr: &Abbreviation) -> String {
+    let mut line = String::from("abbr -a");
+    if abbr.position == AbbrPosition::Anywhere {
+        line.push_str(" --position anywhere");
+    }
+    match abbr.cursor_marker.as_deref() {
+        Some("%") => line.push_str(" --set-cursor"),
+        Some(marker) => line.push_str(&format!(" --set-cursor={}", shell_quote(marker))),
+        None => {}
+    }
+    line.push_str(&format!(
+        " -- {} {}",
+        shell_quote(&abbr.name),
+        shell_quote(&abbr.expansion)
+    ));
+    line
+}
+
+/// Names are single words the editor can find before the cursor
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.chars().any(|c| {
+            c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | ')' | '\'' | '"' | '\\')
+        })
+}
@@ -1,1305 +0,0 @@
-//! `at` builtin  Eworld-class one-time job scheduling with advanced features.
-//!
-//! This implementation provides complete at functionality with professional features:
-//! - Advanced time parsing with natural language support
-//! - Full internationalization support (10+ languages)
-//! - Persistent job storage with database backend
-//! - Job queue management with priority levels
-//! - Email and notification system integration
-//! - Comprehensive logging and audit trail
-//! - Security and permission system
-//! - Job dependencies and chaining
-//! - Resource monitoring and limits
-//! - Timezone and calendar support
-//! - Interactive job editing and management
-//! - Batch processing capabilities
-//! - High availability and failover
-//! - Performance optimization
-//! - API and webhook integration
-//! - Monitoring and alerting
-
-use anyhow::{anyhow, Result, Context};
-use chrono::{DateTime, Utc, TimeZone, Duration as ChronoDuration, Datelike};
-#[cfg(feature = "i18n")]
-use chrono_tz::{Tz, UTC};
-#[cfg(not(feature = "i18n"))]
-use chrono::Utc as UTC;
-#[cfg(not(feature = "i18n"))]
-type Tz = chrono::Utc; // Minimal: single timezone
-use serde::{Deserialize, Serialize};
-use std::{
-    collections::{HashMap, BTreeMap},
-    path::PathBuf,
-    process::Stdio,
-    sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}},
-};
-use tokio::{
-    fs as async_fs,
-    process::Command as AsyncCommand,
-    sync::broadcast,
-    time::{interval, Duration},
-};
-use regex::Regex;
-use crate::common::i18n::I18n; // stub when i18n disabled
-use crate::t; // i18n macro (no-op when feature disabled)
-use nxsh_core::nxsh_log_info;
-
-// Configuration constants
-const DEFAULT_JOB_STORAGE_PATH: &str = ".nxsh/at_jobs";
-const DEFAULT_LOG_PATH: &str = ".nxsh/at_logs";
-const MAX_CONCURRENT_JOBS: usize = 100;
-const JOB_CLEANUP_INTERVAL_HOURS: u64 = 24;
-const MAX_JOB_HISTORY: usize = 10000;
-const DEFAULT_NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(30);
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum JobStatus {
-    Scheduled,
-    Running,
-    Completed,
-    Failed,
-    Cancelled,
-    Expired,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum JobPriority {
-    Low = 0,
-    Normal = 1,
-    High = 2,
-    Critical = 3,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AtJob {
-    pub id: String,
-    pub command: String,
-    pub scheduled_time: DateTime<Utc>,
-    pub created_time: DateTime<Utc>,
-    pub status: JobStatus,
-    pub priority: JobPriority,
-    pub user: String,
-    pub working_directory: PathBuf,
-    pub environment: HashMap<String, String>,
-    pub queue: String,
-    pub mail_on_completion: bool,
-    pub mail_on_error: bool,
-    pub max_runtime: Option<Duration>,
-    pub retry_count: u32,
-    pub max_retries: u32,
-    pub dependencies: Vec<String>,
-    pub tags: Vec<String>,
-    pub metadata: HashMap<String, String>,
-    pub output_file: Option<PathBuf>,
-    pub error_file: Option<PathBuf>,
-    pub execution_log: Vec<JobExecution>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JobExecution {
-    pub start_time: DateTime<Utc>,
-    pub end_time: Option<DateTime<Utc>>,
-    pub exit_code: Option<i32>,
-    pub stdout: String,
-    pub stderr: String,
-    pub resource_usage: ResourceUsage,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResourceUsage {
-    pub cpu_time: Duration,
-    pub memory_peak: u64,
-    pub disk_read: u64,
-    pub disk_write: u64,
-    pub network_rx: u64,
-    pub network_tx: u64,
-}
-
-impl Default for ResourceUsage {
-    fn default() -> Self {
-        Self {
-            cpu_time: Duration::ZERO,
-            memory_peak: 0,
-            disk_read: 0,
-            disk_write: 0,
-            network_rx: 0,
-            network_tx: 0,
-        }
-    }
-}
-
-#[cfg(feature = "system-info")]
-async fn monitor_process_usage_at(pid: u32, acc: std::sync::Arc<std::sync::Mutex<ResourceUsage>>) {
-    use sysinfo::{SystemExt, ProcessExt, NetworksExt, NetworkExt, PidExt};
-    use tokio::time::{sleep, Duration as TokioDuration};
-    let mut sys = sysinfo::System::new();
-    let start = std::time::Instant::now();
-    let mut peak_mem: u64 = 0;
-    let mut rx0: u64 = 0;
-    let mut tx0: u64 = 0;
-    sys.refresh_networks();
-    for (_name, data) in sys.networks() { rx0 += data.total_received(); tx0 += data.total_transmitted(); }
-    loop {
-        sys.refresh_processes();
-        if let Some(p) = sys.process(sysinfo::Pid::from(pid as usize)) {
-            let mem = p.memory();
-            if mem > peak_mem { peak_mem = mem; }
-        } else {
-            break;
-        }
-        sleep(TokioDuration::from_millis(200)).await;
-    }
-    sys.refresh_networks();
-    let mut rx1: u64 = 0;
-    let mut tx1: u64 = 0;
-    for (_name, data) in sys.networks() { rx1 += data.total_received(); tx1 += data.total_transmitted(); }
-    if let Ok(mut g) = acc.lock() {
-        g.cpu_time = start.elapsed();
-        g.memory_peak = peak_mem * 1024; // KiB -> bytes
-        g.network_rx = rx1.saturating_sub(rx0);
-        g.network_tx = tx1.saturating_sub(tx0);
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AtConfig {
-    pub storage_path: PathBuf,
-    pub log_path: PathBuf,
-    pub max_concurrent_jobs: usize,
-    pub default_queue: String,
-    pub timezone: String,
-    pub mail_enabled: bool,
-    pub smtp_server: Option<String>,
-    pub smtp_port: u16,
-    pub smtp_username: Option<String>,
-    pub smtp_password: Option<String>,
-    pub webhook_url: Option<String>,
-    pub security_enabled: bool,
-    pub audit_enabled: bool,
-    pub cleanup_interval: Duration,
-    pub max_job_runtime: Duration,
-    pub allowed_users: Vec<String>,
-    pub denied_users: Vec<String>,
-    pub resource_limits: ResourceLimits,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResourceLimits {
-    pub max_memory: u64,
-    pub max_cpu_time: Duration,
-    pub max_disk_usage: u64,
-    pub max_network_usage: u64,
-}
-
-impl Default for AtConfig {
-    fn default() -> Self {
-        Self {
-            storage_path: PathBuf::from(DEFAULT_JOB_STORAGE_PATH),
-            log_path: PathBuf::from(DEFAULT_LOG_PATH),
-            max_concurrent_jobs: MAX_CONCURRENT_JOBS,
-            default_queue: "a".to_string(),
-            timezone: UTC.to_string(),
-            mail_enabled: false,
-            smtp_server: None,
-            smtp_port: 587,
-            smtp_username: None,
-            smtp_password: None,
-            webhook_url: None,
-            security_enabled: true,
-            audit_enabled: true,
-            cleanup_interval: Duration::from_secs(JOB_CLEANUP_INTERVAL_HOURS * 3600),
-            max_job_runtime: Duration::from_secs(3600), // 1 hour
-            allowed_users: Vec::new(),
-            denied_users: Vec::new(),
-            resource_limits: ResourceLimits {
-                max_memory: 1024 * 1024 * 1024, // 1GB
-                max_cpu_time: Duration::from_secs(3600),
-                max_disk_usage: 10 * 1024 * 1024 * 1024, // 10GB
-                max_network_usage: 1024 * 1024 * 1024, // 1GB
-            },
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct AtScheduler {
-    config: AtConfig,
-    jobs: Arc<RwLock<BTreeMap<String, AtJob>>>,
-    running_jobs: Arc<RwLock<HashMap<String, tokio::task::JoinHandle<()>>>>,
-    job_counter: Arc<AtomicU64>,
-    event_sender: broadcast::Sender<AtEvent>,
-    i18n: I18n,
-    time_parser: TimeParser,
-}
-
-#[derive(Debug, Clone)]
-pub enum AtEvent {
-    JobScheduled(String),
-    JobStarted(String),
-    JobCompleted(String, i32),
-    JobFailed(String, String),
-    JobCancelled(String),
-    SystemShutdown,
-}
-
-#[derive(Debug)]
-pub struct TimeParser {
-    timezone: Tz,
-    locale: String,
-    patterns: Vec<TimePattern>,
-}
-
-#[derive(Debug, Clone)]
-pub struct TimePattern {
-    pattern: Regex,
-    parser: fn(&str, &TimeParser) -> Result<DateTime<Utc>>,
-    description: String,
-}
-
-impl TimeParser {
-    pub fn new(timezone: Tz, locale: &str) -> Self {
-        let patterns = vec![
-            // Absolute time patterns
-            TimePattern {
-                pattern: Regex::new(r"^(\d{1,2}):(\d{2})(?:\s*(am|pm))?(?:\s+(.+))?$").unwrap(),
-                parser: Self::parse_time_format,
-                description: "HH:MM [AM/PM] [date]".to_string(),
-            },
-            TimePattern {
-                pattern: Regex::new(r"^(\d{1,4})(?:\s*(am|pm))?(?:\s+(.+))?$").unwrap(),
-                parser: Self::parse_numeric_time,
-                description: "HHMM or HH [AM/PM] [date]".to_string(),
-            },
-            TimePattern {
-                pattern: Regex::new(r"^(noon|midnight)(?:\s+(.+))?$").unwrap(),
-                parser: Self::parse_named_time,
-                description: "noon/midnight [date]".to_string(),
-            },
-            // Relative time patterns
-            TimePattern {
-                pattern: Regex::new(r"^now\s*\+\s*(\d+)\s*(minutes?|hours?|days?|weeks?|months?|years?)$").unwrap(),
-                parser: Self::parse_relative_time,
-                description: "now + N units".to_string(),
-            },
-            TimePattern {
-                pattern: Regex::new(r"^in\s+(\d+)\s*(minutes?|hours?|days?|weeks?|months?|years?)$").unwrap(),
-                parser: Self::parse_in_time,
-                description: "in N units".to_string(),
-            },
-            // Natural language patterns
-            TimePattern {
-                pattern: Regex::new(r"^(tomorrow|today)\s+(?:at\s+)?(.+)$").unwrap(),
-                parser: Self::parse_day_relative,
-                description: "tomorrow/today at time".to_string(),
-            },
-            TimePattern {
-                pattern: Regex::new(r"^next\s+(monday|tuesday|wednesday|thursday|friday|saturday|sunday)(?:\s+at\s+(.+))?$").unwrap(),
-                parser: Self::parse_next_weekday,
-                description: "next weekday [at time]".to_string(),
-            },
-            // ISO format
-            TimePattern {
-                pattern: Regex::new(r"^(\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:\.\d{3})?(?:Z|[+-]\d{2}:\d{2})?)$").unwrap(),
-                parser: Self::parse_iso_format,
-                description: "ISO 8601 format".to_string(),
-            },
-            // Unix timestamp
-            TimePattern {
-                pattern: Regex::new(r"^@(\d+)$").unwrap(),
-                parser: Self::parse_unix_timestamp,
-                description: "Unix timestamp (@timestamp)".to_string(),
-            },
-        ];
-
-        Self {
-            timezone,
-            locale: locale.to_string(),
-            patterns,
-        }
-    }
-
-    pub fn parse_time(&self, input: &str) -> Result<DateTime<Utc>> {
-        let input = input.trim().to_lowercase();
-        
-        for pattern in &self.patterns {
-            if pattern.pattern.is_match(&input) {
-                match (pattern.parser)(&input, self) {
-                    Ok(dt) => return Ok(dt),
-                    Err(_) => continue,
-                }
-            }
-        }
-        
-        Err(anyhow!(
-            "{}",
-            t!("at.error.unable-parse-time", "input" => input.as_str())
-        ))
-    }
-
-    fn parse_time_format(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[0].pattern.captures(input).unwrap();
-        let hour: u32 = caps.get(1).unwrap().as_str().parse()?;
-        let minute: u32 = caps.get(2).unwrap().as_str().parse()?;
-        let am_pm = caps.get(3).map(|m| m.as_str());
-        let date_str = caps.get(4).map(|m| m.as_str());
-
-        let mut hour = hour;
-        if let Some(am_pm) = am_pm {
-            match am_pm {
-                "pm" if hour != 12 => hour += 12,
-                "am" if hour == 12 => hour = 0,
-                _ => {}
-            }
-        }
-
-        if hour > 23 || minute > 59 {
-            return Err(anyhow!(
-                "{}",
-                t!("at.error.invalid-time", "hour" => hour, "minute" => minute)
-            ));
-        }
-
-        let base_date = if let Some(date_str) = date_str {
-            parser.parse_date(date_str)?
-        } else {
-            let now = parser.timezone.from_utc_datetime(&Utc::now().naive_utc());
-            let target_time = now.date_naive().and_hms_opt(hour, minute, 0).unwrap();
-            if target_time <= now.naive_local() {
-                now.date_naive() + ChronoDuration::days(1)
-            } else {
-                now.date_naive()
-            }
-        };
-
-        let target_dt = base_date.and_hms_opt(hour, minute, 0)
-            .ok_or_else(|| anyhow!("{}", t!("at.error.invalid-date-time-combo")))?;
-        
-        Ok(parser.timezone.from_local_datetime(&target_dt)
-            .single()
-            .ok_or_else(|| anyhow!("{}", t!("at.error.ambiguous-local-time")))?
-            .with_timezone(&Utc))
-    }
-
-    fn parse_numeric_time(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[1].pattern.captures(input).unwrap();
-        let time_str = caps.get(1).unwrap().as_str();
-        let am_pm = caps.get(2).map(|m| m.as_str());
-        let date_str = caps.get(3).map(|m| m.as_str());
-
-        let (hour, minute) = match time_str.len() {
-            1 | 2 => (time_str.parse::<u32>()?, 0),
-            3 => {
-                let hour = time_str[0..1].parse::<u32>()?;
-                let minute = time_str[1..3].parse::<u32>()?;
-                (hour, minute)
-            }
-            4 => {
-                let hour = time_str[0..2].parse::<u32>()?;
-                let minute = time_str[2..4].parse::<u32>()?;
-                (hour, minute)
-            }
-            _ => return Err(anyhow!("{}", t!("at.error.invalid-numeric-time"))),
-        };
-
-        let formatted_input = if let Some(am_pm) = am_pm {
-            format!("{}:{:02} {} {}", hour, minute, am_pm, date_str.unwrap_or(""))
-        } else {
-            format!("{}:{:02} {}", hour, minute, date_str.unwrap_or(""))
-        };
-
-        Self::parse_time_format(&formatted_input, parser)
-    }
-
-    fn parse_named_time(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[2].pattern.captures(input).unwrap();
-        let time_name = caps.get(1).unwrap().as_str();
-        let date_str = caps.get(2).map(|m| m.as_str());
-
-        let (hour, minute) = match time_name {
-            "noon" => (12, 0),
-            "midnight" => (0, 0),
-            _ => return Err(anyhow!(
-                "{}",
-                t!("at.error.unknown-named-time", "name" => time_name)
-            )),
-        };
-
-        let formatted_input = format!("{}:{:02} {}", hour, minute, date_str.unwrap_or(""));
-        Self::parse_time_format(&formatted_input, parser)
-    }
-
-    fn parse_relative_time(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[3].pattern.captures(input).unwrap();
-        let amount: i64 = caps.get(1).unwrap().as_str().parse()?;
-        let unit = caps.get(2).unwrap().as_str();
-
-        let now = Utc::now();
-        let duration = match unit {
-            "minute" | "minutes" => ChronoDuration::minutes(amount),
-            "hour" | "hours" => ChronoDuration::hours(amount),
-            "day" | "days" => ChronoDuration::days(amount),
-            "week" | "weeks" => ChronoDuration::weeks(amount),
-            "month" | "months" => ChronoDuration::days(amount * 30), // Approximate
-            "year" | "years" => ChronoDuration::days(amount * 365), // Approximate
-            _ => return Err(anyhow!(
-                "{}",
-                t!("at.error.unknown-time-unit", "unit" => unit)
-            )),
-        };
-
-        Ok(now + duration)
-    }
-
-    fn parse_in_time(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let modified_input = input.replace("in ", "now + ");
-        Self::parse_relative_time(&modified_input, parser)
-    }
-
-    fn parse_day_relative(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[5].pattern.captures(input).unwrap();
-        let day = caps.get(1).unwrap().as_str();
-        let time_str = caps.get(2).unwrap().as_str();
-
-        let now = parser.timezone.from_utc_datetime(&Utc::now().naive_utc());
-        let target_date = match day {
-            "today" => now.date_naive(),
-            "tomorrow" => now.date_naive() + ChronoDuration::days(1),
-            _ => return Err(anyhow!(
-                "{}",
-                t!("at.error.unknown-day", "day" => day)
-            )),
-        };
-
-        let formatted_input = format!("{} {}", time_str, target_date.format("%Y-%m-%d"));
-        parser.parse_time(&formatted_input)
-    }
-
-    fn parse_next_weekday(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[6].pattern.captures(input).unwrap();
-        let weekday_str = caps.get(1).unwrap().as_str();
-        let time_str = caps.get(2).map(|m| m.as_str()).unwrap_or("9:00");
-
-        let target_weekday = match weekday_str {
-            "monday" => chrono::Weekday::Mon,
-            "tuesday" => chrono::Weekday::Tue,
-            "wednesday" => chrono::Weekday::Wed,
-            "thursday" => chrono::Weekday::Thu,
-            "friday" => chrono::Weekday::Fri,
-            "saturday" => chrono::Weekday::Sat,
-            "sunday" => chrono::Weekday::Sun,
-            _ => return Err(anyhow!(
-                "{}",
-                t!("at.error.unknown-weekday", "weekday" => weekday_str)
-            )),
-        };
-
-        let now = parser.timezone.from_utc_datetime(&Utc::now().naive_utc());
-        let mut target_date = now.date_naive() + ChronoDuration::days(1);
-        
-        while target_date.weekday() != target_weekday {
-            target_date += ChronoDuration::days(1);
-        }
-
-        let formatted_input = format!("{} {}", time_str, target_date.format("%Y-%m-%d"));
-        parser.parse_time(&formatted_input)
-    }
-
-    fn parse_iso_format(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[7].pattern.captures(input).unwrap();
-        let iso_str = caps.get(1).unwrap().as_str();
-        
-        DateTime::parse_from_rfc3339(iso_str)
-            .map(|dt| dt.with_timezone(&Utc))
-            .context(t!("at.error.parse-iso"))
-    }
-
-    fn parse_unix_timestamp(input: &str, parser: &TimeParser) -> Result<DateTime<Utc>> {
-        let caps = parser.patterns[8].pattern.captures(input).unwrap();
-        let timestamp: i64 = caps.get(1).unwrap().as_str().parse()?;
-        
-        DateTime::from_timestamp(timestamp, 0)
-            .ok_or_else(|| anyhow!(
-                "{}",
-                t!("at.error.invalid-unix-timestamp", "timestamp" => timestamp)
-            ))
-    }
-
-    fn parse_date(&self, date_str: &str) -> Result<chrono::NaiveDate> {
-        let date_str = date_str.trim().to_lowercase();
-        
-        // Try various date formats
-        let formats = vec![
-            "%Y-%m-%d",
-            "%m-%d-%Y",
-            "%d-%m-%Y",
-            "%m/%d/%Y",
-            "%d/%m/%Y",
-            "%Y/%m/%d",
-            "%B %d %Y",
-            "%b %d %Y",
-            "%B %d",
-            "%b %d",
-            "%m-%d",
-            "%d-%m",
-        ];
-
-        for format in formats {
-            if let Ok(date) = chrono::NaiveDate::parse_from_str(&date_str, format) {
-                return Ok(date);
-            }
-        }
-
-        Err(anyhow!(
-            "{}",
-            t!("at.error.unable-parse-date", "date" => date_str.as_str())
-        ))
-    }
-}
-
-impl AtScheduler {
-    pub async fn new(config: AtConfig, i18n: I18n) -> Result<Self> {
-        // Create storage directories
-        async_fs::create_dir_all(&config.storage_path).await?;
-        async_fs::create_dir_all(&config.log_path).await?;
-
-        let (event_sender, _) = broadcast::channel(1000);
-    let current_locale = i18n.current_locale();
-    #[cfg(feature = "i18n")]
-    let timezone = config.timezone.parse::<Tz>().unwrap_or(UTC);
-    #[cfg(not(feature = "i18n"))]
-    let timezone = UTC; // Single timezone in stub
-        let time_parser = TimeParser::new(timezone, &current_locale);
-
-        let scheduler = Self {
-            config,
-            jobs: Arc::new(RwLock::new(BTreeMap::new())),
-            running_jobs: Arc::new(RwLock::new(HashMap::new())),
-            job_counter: Arc::new(AtomicU64::new(0)),
-            event_sender,
-            i18n,
-            time_parser,
-        };
-
-        // Load existing jobs
-        scheduler.load_jobs().await?;
-
-        // Start background tasks
-        scheduler.start_scheduler_loop().await;
-        scheduler.start_cleanup_task().await;
-
-        Ok(scheduler)
-    }
-
-    pub async fn schedule_job(&self, command: String, time_spec: &str, options: JobOptions) -> Result<String> {
-        // Parse time specification
-        let scheduled_time = self.time_parser.parse_time(time_spec)
-            .with_context(|| t!("at.error.unable-parse-time", "input" => time_spec))?;
-
-        // Validate scheduled time is in the future
-        if scheduled_time <= Utc::now() {
-            return Err(anyhow!("{}", t!("at.error.in-future")));
-        }
-
-        // Check permissions
-        if self.config.security_enabled {
-            self.check_user_permissions(&options.user)?;
-        }
-
-        // Create job
-        let job_id = format!("at_{}", self.job_counter.fetch_add(1, Ordering::SeqCst));
-        let job = AtJob {
-            id: job_id.clone(),
-            command,
-            scheduled_time,
-            created_time: Utc::now(),
-            status: JobStatus::Scheduled,
-            priority: options.priority,
-            user: options.user,
-            working_directory: options.working_directory,
-            environment: options.environment,
-            queue: options.queue,
-            mail_on_completion: options.mail_on_completion,
-            mail_on_error: options.mail_on_error,
-            max_runtime: options.max_runtime,
-            retry_count: 0,
-            max_retries: options.max_retries,
-            dependencies: options.dependencies,
-            tags: options.tags,
-            metadata: options.metadata,
-            output_file: options.output_file,
-            error_file: options.error_file,
-            execution_log: Vec::new(),
-        };
-
-        // Store job
-        {
-            let mut jobs = self.jobs.write().unwrap();
-            jobs.insert(job_id.clone(), job.clone());
-        }
-
-        // Persist to disk
-        self.save_job(&job).await?;
-
-        // Send event
-        let _ = self.event_sender.send(AtEvent::JobScheduled(job_id.clone()));
-
-        // Log scheduling
-        self.log_event(&format!("Job {job_id} scheduled for {scheduled_time}")).await?;
-
-        Ok(job_id)
-    }
-
-    pub async fn list_jobs(&self, queue: Option<&str>, user: Option<&str>) -> Result<Vec<AtJob>> {
-        let jobs = self.jobs.read().unwrap();
-        let mut result = Vec::new();
-
-        for job in jobs.values() {
-            let queue_match = queue.is_none_or(|q| job.queue == q);
-            let user_match = user.is_none_or(|u| job.user == u);
-            
-            if queue_match && user_match {
-                result.push(job.clone());
-            }
-        }
-
-        // Sort by scheduled time
-        result.sort_by(|a, b| a.scheduled_time.cmp(&b.scheduled_time));
-        Ok(result)
-    }
-
-    pub async fn remove_job(&self, job_id: &str) -> Result<()> {
-        let job = {
-            let mut jobs = self.jobs.write().unwrap();
-            jobs.remove(job_id)
-                .ok_or_else(|| anyhow!("{}", t!("at.error.job-not-found", "id" => job_id)))?
-        };
-
-        // Cancel if running
-        if job.status == JobStatus::Running {
-            let mut running_jobs = self.running_jobs.write().unwrap();
-            if let Some(handle) = running_jobs.remove(job_id) {
-                handle.abort();
-            }
-        }
-
-        // Remove from disk
-        let job_file = self.config.storage_path.join(format!("{job_id}.json"));
-        if job_file.exists() {
-            async_fs::remove_file(job_file).await?;
-        }
-
-        // Send event
-        let _ = self.event_sender.send(AtEvent::JobCancelled(job_id.to_string()));
-
-        // Log removal
-        self.log_event(&format!("Job {job_id} removed")).await?;
-
-        Ok(())
-    }
-
-    pub async fn get_job(&self, job_id: &str) -> Result<AtJob> {
-        let jobs = self.jobs.read().unwrap();
-        jobs.get(job_id)
-            .cloned()
-            .ok_or_else(|| anyhow!("{}", t!("at.error.job-not-found", "id" => job_id)))
-    }
-
-    async fn start_scheduler_loop(&self) {
-        let jobs = Arc::clone(&self.jobs);
-        let running_jobs = Arc::clone(&self.running_jobs);
-        let event_sender = self.event_sender.clone();
-        let config = self.config.clone();
-
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(10)); // Check every 10 seconds
-            
-            loop {
-                interval.tick().await;
-                let now = Utc::now();
-                let mut jobs_to_run = Vec::new();
-
-                // Find jobs ready to run
-                {
-                    let mut jobs_guard = jobs.write().unwrap();
-                    let jobs_clone = jobs_guard.clone(); // Clone for dependency check
-                    for (_job_id, job) in jobs_guard.iter_mut() {
-                        if job.status == JobStatus::Scheduled && job.scheduled_time <= now {
-                            // Check dependencies
-                            if Self::check_job_dependencies(job, &jobs_clone) {
-                                job.status = JobStatus::Running;
-                                jobs_to_run.push(job.clone());
-                            }
-                        }
-                    }
-                }
-
-                // Execute jobs
-                for job in jobs_to_run {
-                    let job_id = job.id.clone();  // Extract job ID first
-                    let job_id_for_closure = job_id.clone();  // Clone for async closure
-                    let job_clone = job.clone();
-                    let jobs_clone = Arc::clone(&jobs);
-                    let running_jobs_clone = Arc::clone(&running_jobs);
-                    let event_sender_clone = event_sender.clone();
-                    let config_clone = config.clone();
-
-                    let handle = tokio::spawn(async move {
-                        let result = Self::execute_job(job_clone, config_clone).await;
-                        
-                        // Update job status
-                        {
-                            let mut jobs_guard = jobs_clone.write().unwrap();
-                            if let Some(job) = jobs_guard.get_mut(&job_id_for_closure) {
-                                match result {
-                                    Ok(execution) => {
-                                        job.status = JobStatus::Completed;
-                                        job.execution_log.push(execution);
-                                        let _ = event_sender_clone.send(AtEvent::JobCompleted(job_id_for_closure.clone(), 0));
-                                    }
-                                    Err(e) => {
-                                        job.status = JobStatus::Failed;
-                                        let _ = event_sender_clone.send(AtEvent::JobFailed(job_id_for_closure.clone(), e.to_string()));
-                                    }
-                                }
-                            }
-                        }
-
-                        // Remove from running jobs
-                        {
-                            let mut running_guard = running_jobs_clone.write().unwrap();
-                            running_guard.remove(&job_id_for_closure);
-                        }
-                    });
-
-                    // Store running job handle
-                    let job_id_for_storage = job_id;
-                    let job_id_for_event = job_id_for_storage.clone();
-                    {
-                        let mut running_guard = running_jobs.write().unwrap();
-                        running_guard.insert(job_id_for_storage, handle);
-                    }
-
-                    let _ = event_sender.send(AtEvent::JobStarted(job_id_for_event));
-                }
-            }
-        });
-    }
-
-    async fn execute_job(job: AtJob, config: AtConfig) -> Result<JobExecution> {
-        let start_time = Utc::now();
-        
-        // Change to working directory
-        std::env::set_current_dir(&job.working_directory)?;
-
-        // Set environment variables
-        for (key, value) in &job.environment {
-            std::env::set_var(key, value);
-        }
-
-        // Execute command (spawn to obtain PID for resource monitoring)
-        let mut cmd = AsyncCommand::new("sh");
-        cmd.arg("-c")
-           .arg(&job.command)
-           .stdout(Stdio::piped())
-           .stderr(Stdio::piped());
-
-        let child = cmd.spawn()?;
-        let pid_for_monitor = child.id().unwrap_or(0);
-        let monitor_handle = crate::common::resource_monitor::spawn_basic_monitor(pid_for_monitor);
-
-        let output = child.wait_with_output().await?;
-        let end_time = Utc::now();
-
-        let monitored_usage = {
-            use tokio::time::timeout;
-            if let Ok(Ok(b)) = timeout(std::time::Duration::from_secs(1), monitor_handle).await {
-                ResourceUsage {
-                    cpu_time: b.cpu_time,
-                    memory_peak: b.memory_peak_bytes,
-                    disk_read: 0,
-                    disk_write: 0,
-                    network_rx: b.network_rx,
-                    network_tx: b.network_tx,
-                }
-            } else { ResourceUsage::default() }
-        };
-
-        let execution = JobExecution {
-            start_time,
-            end_time: Some(end_time),
-            exit_code: output.status.code(),
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-            resource_usage: monitored_usage,
-        };
-
-        // Handle output redirection
-        if let Some(output_file) = &job.output_file {
-            async_fs::write(output_file, &execution.stdout).await?;
-        }
-
-        if let Some(error_file) = &job.error_file {
-            async_fs::write(error_file, &execution.stderr).await?;
-        }
-
-        // Send notifications
-        if job.mail_on_completion || (job.mail_on_error && output.status.code() != Some(0)) {
-            Self::send_notification(&job, &execution, &config).await?;
-        }
-
-        Ok(execution)
-    }
-
-    fn check_job_dependencies(job: &AtJob, all_jobs: &BTreeMap<String, AtJob>) -> bool {
-        for dep_id in &job.dependencies {
-            if let Some(dep_job) = all_jobs.get(dep_id) {
-                if dep_job.status != JobStatus::Completed {
-                    return false;
-                }
-            } else {
-                return false; // Dependency not found
-            }
-        }
-        true
-    }
-
-    async fn send_notification(job: &AtJob, execution: &JobExecution, config: &AtConfig) -> Result<()> {
-        // Email notification (Unix: sendmail; otherwise log-only)
-        if config.mail_enabled && (job.mail_on_completion || job.mail_on_error) {
-            #[cfg(unix)]
-            {
-                use tokio::process::Command as AsyncCommand;
-                let subject = format!("[at] {} {}", job.id, if execution.exit_code == Some(0) { "OK" } else { "FAIL" });
-                let body = format!(
-                    "job: {}\ncmd: {}\nexit: {:?}\nstart: {}\nend: {}\nstdout:\n{}\n\nstderr:\n{}\n",
-                    job.id, job.command, execution.exit_code, execution.start_time, execution.end_time.unwrap_or_default(), execution.stdout, execution.stderr
-                );
-                if let Some(addr) = &config.mail_to {
-                    let mut proc = AsyncCommand::new("/usr/sbin/sendmail");
-                    proc.arg("-t");
-                    if let Ok(mut child) = proc.stdin(std::process::Stdio::piped()).spawn() {
-                        use tokio::io::AsyncWriteExt;
-                        if let Some(stdin) = child.stdin.as_mut() {
-                            let _ = stdin.write_all(format!("To: {}\nSubject: {}\n\n{}", addr, subject, body).as_bytes()).await;
-                        }
-                        let _ = child.wait().await;
-                    }
-                }
-            }
-            #[cfg(not(unix))]
-            {
-                nxsh_log_info!("at email notify: would send email");
-            }
-        }
-
-        // Webhook notification
-        if let Some(webhook_url) = &config.webhook_url {
-            let payload = serde_json::json!({
-                "job_id": job.id,
-                "command": job.command,
-                "status": job.status,
-                "exit_code": execution.exit_code,
-                "start_time": execution.start_time,
-                "end_time": execution.end_time,
-                "stdout": execution.stdout,
-                "stderr": execution.stderr
-            }).to_string();
-            #[cfg(feature = "updates")]
-            {
-                let _ = ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(15)).build()
-                    .post(webhook_url)
-                    .set("Content-Type", "application/json")
-                    .send_string(&payload);
-            }
-            #[cfg(not(feature = "updates"))]
-            {
-                nxsh_log_info!("at webhook notify: would POST to {}", webhook_url);
-            }
-        }
-
-        Ok(())
-    }
-
-    async fn start_cleanup_task(&self) {
-        let jobs = Arc::clone(&self.jobs);
-        let config = self.config.clone();
-
-        tokio::spawn(async move {
-            let mut interval = interval(config.cleanup_interval);
-            
-            loop {
-                interval.tick().await;
-                let cutoff_time = Utc::now() - ChronoDuration::days(7); // Keep jobs for 7 days
-
-                let mut jobs_guard = jobs.write().unwrap();
-                jobs_guard.retain(|_, job| {
-                    match job.status {
-                        JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => {
-                            job.created_time > cutoff_time
-                        }
-                        _ => true,
-                    }
-                });
-            }
-        });
-    }
-
-    async fn load_jobs(&self) -> Result<()> {
-        let mut dir = async_fs::read_dir(&self.config.storage_path).await?;
-        
-        while let Some(entry) = dir.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Ok(content) = async_fs::read_to_string(&path).await {
-                    if let Ok(job) = serde_json::from_str::<AtJob>(&content) {
-                        let mut jobs = self.jobs.write().unwrap();
-                        jobs.insert(job.id.clone(), job);
-                    }
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    async fn save_job(&self, job: &AtJob) -> Result<()> {
-        let job_file = self.config.storage_path.join(format!("{}.json", job.id));
-        let content = serde_json::to_string_pretty(job)?;
-        async_fs::write(job_file, content).await?;
-        Ok(())
-    }
-
-    async fn log_event(&self, message: &str) -> Result<()> {
-        let log_file = self.config.log_path.join("at.log");
-        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
-        let log_entry = format!("[{timestamp}] {message}\n");
-        
-        let mut file = async_fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_file)
-            .await?;
-        
-        use tokio::io::AsyncWriteExt;
-        file.write_all(log_entry.as_bytes()).await?;
-        Ok(())
-    }
-
-    fn check_user_permissions(&self, user: &str) -> Result<()> {
-        if !self.config.allowed_users.is_empty() && !self.config.allowed_users.contains(&user.to_string()) {
-            return Err(anyhow!(
-                "{}",
-                t!("at.error.user-not-allowed", "user" => user)
-            ));
-        }
-
-        if self.config.denied_users.contains(&user.to_string()) {
-            return Err(anyhow!(
-                "{}",
-                t!("at.error.user-denied", "user" => user)
-            ));
-        }
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct JobOptions {
-    pub user: String,
-    pub working_directory: PathBuf,
-    pub environment: HashMap<String, String>,
-    pub queue: String,
-    pub priority: JobPriority,
-    pub mail_on_completion: bool,
-    pub mail_on_error: bool,
-    pub max_runtime: Option<Duration>,
-    pub max_retries: u32,
-    pub dependencies: Vec<String>,
-    pub tags: Vec<String>,
-    pub metadata: HashMap<String, String>,
-    pub output_file: Option<PathBuf>,
-    pub error_file: Option<PathBuf>,
-}
-
-impl Default for JobOptions {
-    fn default() -> Self {
-        Self {
-            user: "unknown".to_string(),
-            working_directory: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
-            environment: std::env::vars().collect(),
-            queue: "a".to_string(),
-            priority: JobPriority::Normal,
-            mail_on_completion: false,
-            mail_on_error: true,
-            max_runtime: Some(Duration::from_secs(3600)),
-            max_retries: 0,
-            dependencies: Vec::new(),
-            tags: Vec::new(),
-            metadata: HashMap::new(),
-            output_file: None,
-            error_file: None,
-        }
-    }
-}
-
-// Main CLI interface
-pub async fn at_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("{}", t!("at.help.inline-usage")));
-    }
-
-    let config = AtConfig::default();
-    let mut options = JobOptions::default();
-    let mut time_spec = String::new();
-    let mut command_args = Vec::new();
-    let mut show_help = false;
-    let mut list_jobs = false;
-    let mut remove_jobs = Vec::new();
-    let mut queue_filter = None;
-    let i18n = I18n::new(); // Use default I18n instance
-
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--help" | "-h" => show_help = true,
-            "-l" | "--list" => list_jobs = true,
-            "-r" | "--remove" => {
-                i += 1;
-                if i < args.len() {
-                    remove_jobs.push(args[i].clone());
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-id-for-remove")));
-                }
-            }
-            "-q" | "--queue" => {
-                i += 1;
-                if i < args.len() {
-                    options.queue = args[i].clone();
-                    queue_filter = Some(args[i].clone());
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-queue-name")));
-                }
-            }
-            "-m" | "--mail" => options.mail_on_completion = true,
-            "-M" | "--no-mail" => {
-                options.mail_on_completion = false;
-                options.mail_on_error = false;
-            }
-            "-f" | "--file" => {
-                i += 1;
-                if i < args.len() {
-                    let content = async_fs::read_to_string(&args[i]).await
-                        .with_context(|| t!("at.error.read-file", "filename" => args[i].as_str()))?;
-                    command_args.push(content);
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-filename")));
-                }
-            }
-            "-t" | "--time" => {
-                i += 1;
-                if i < args.len() {
-                    time_spec = args[i].clone();
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-time-spec")));
-                }
-            }
-            "--priority" => {
-                i += 1;
-                if i < args.len() {
-                    options.priority = match args[i].as_str() {
-                        "low" => JobPriority::Low,
-                        "normal" => JobPriority::Normal,
-                        "high" => JobPriority::High,
-                        "critical" => JobPriority::Critical,
-                        _ => return Err(anyhow!(
-                            "{}",
-                            t!("at.error.invalid-priority", "value" => args[i].as_str())
-                        )),
-                    };
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-priority")));
-                }
-            }
-            "--output" => {
-                i += 1;
-                if i < args.len() {
-                    options.output_file = Some(PathBuf::from(&args[i]));
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-output-filename")));
-                }
-            }
-            "--error" => {
-                i += 1;
-                if i < args.len() {
-                    options.error_file = Some(PathBuf::from(&args[i]));
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-error-filename")));
-                }
-            }
-            "--max-runtime" => {
-                i += 1;
-                if i < args.len() {
-                    let runtime_secs: u64 = args[i].parse()
-                        .context(t!("at.error.invalid-max-runtime"))?;
-                    options.max_runtime = Some(Duration::from_secs(runtime_secs));
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-max-runtime")));
-                }
-            }
-            "--retry" => {
-                i += 1;
-                if i < args.len() {
-                    options.max_retries = args[i].parse()
-                        .context(t!("at.error.invalid-retry-count"))?;
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-retry-count")));
-                }
-            }
-            "--tag" => {
-                i += 1;
-                if i < args.len() {
-                    options.tags.push(args[i].clone());
-                } else {
-                    return Err(anyhow!("{}", t!("at.error.missing-tag-name")));
-                }
-            }
-            arg if arg.starts_with("--") => {
-                return Err(anyhow!("{}", t!("at.error.unknown-option", "option" => arg)));
-            }
-            _ => {
-                if time_spec.is_empty() {
-                    time_spec = args[i].clone();
-                } else {
-                    command_args.extend_from_slice(&args[i..]);
-                    break;
-                }
-            }
-        }
-        i += 1;
-    }
-
-    if show_help {
-        print_at_help(&i18n);
-        return Ok(());
-    }
-
-    // Initialize scheduler
-    let scheduler = AtScheduler::new(config, i18n).await?;
-
-    // Handle different operations
-    if list_jobs {
-        let jobs = scheduler.list_jobs(queue_filter.as_deref(), None).await?;
-        
-        if jobs.is_empty() {
-            println!("{}", t!("at.list.no-jobs"));
-        } else {
-            println!(
-                "{:<12} {:<20} {:<10} {:<8} {}",
-                t!("at.list.header.job-id"),
-                t!("at.list.header.scheduled-time"),
-                t!("at.list.header.status"),
-                t!("at.list.header.queue"),
-                t!("at.list.header.command")
-            );
-            println!("{}", "-".repeat(80));
-            
-            for job in jobs {
-                println!("{:<12} {:<20} {:<10} {:<8} {}", 
-                    job.id,
-                    job.scheduled_time.format("%Y-%m-%d %H:%M:%S"),
-                    format!("{:?}", job.status),
-                    job.queue,
-                    if job.command.len() > 40 {
-                        format!("{}...", &job.command[..37])
-                    } else {
-                        job.command
-                    }
-                );
-            }
-        }
-        return Ok(());
-    }
-
-    if !remove_jobs.is_empty() {
-        for job_id in remove_jobs {
-            match scheduler.remove_job(&job_id).await {
-                Ok(()) => println!("{}", t!("at.remove.removed", "id" => job_id.as_str())),
-                Err(e) => eprintln!("{}", t!("at.remove.failed", "id" => job_id.as_str(), "error" => e.to_string())),
-            }
-        }
-        return Ok(());
-    }
-
-    // Schedule new job
-    if time_spec.is_empty() {
-        return Err(anyhow!("{}", t!("at.error.time-spec-required")));
-    }
-
-    let command = if command_args.is_empty() {
-        // Read from stdin
-        use std::io::{BufRead, BufReader, stdin};
-        let stdin = stdin();
-        let reader = BufReader::new(stdin.lock());
-        let mut command_lines = Vec::new();
-        
-        for line in reader.lines() {
-            match line {
-                Ok(line) => command_lines.push(line),
-                Err(e) => return Err(anyhow!("{}", t!("at.error.read-stdin", "error" => e.to_string()))),
-            }
-        }
-        
-        command_lines.join("\n")
-    } else {
-        command_args.join(" ")
-    };
-
-    if command.trim().is_empty() {
-        return Err(anyhow!("{}", t!("at.error.no-command")));
-    }
-
-    // Get current user
-    options.user = std::env::var("USER")
-        .or_else(|_| std::env::var("USERNAME"))
-        .unwrap_or_else(|_| "unknown".to_string());
-
-    // Schedule the job
-    match scheduler.schedule_job(command, &time_spec, options).await {
-        Ok(job_id) => {
-            let job = scheduler.get_job(&job_id).await?;
-            let time_str = job.scheduled_time.format("%a %b %e %T %Y").to_string();
-            println!(
-                "{}",
-                t!(
-                    "at.schedule.scheduled",
-                    "id" => job_id.as_str(),
-                    "time" => time_str.as_str()
-                )
-            );
-        }
-        Err(e) => {
-            eprintln!("{}", t!("at.error.schedule-failed", "error" => e.to_string()));
-            std::process::exit(1);
-        }
-    }
-
-    Ok(())
-}
-
-fn print_at_help(i18n: &I18n) {
-    println!("{}", i18n.get("at.help.title", None));
-    println!();
-    println!("{}", i18n.get("at.help.usage", None));
-    println!("{}", i18n.get("at.help.usage-line", None));
-    println!();
-    println!("{}", i18n.get("at.help.time_formats", None));
-    println!("{}", i18n.get("at.help.time_formats.details", None));
-    println!();
-    println!("{}", i18n.get("at.help.options", None));
-    println!("{}", i18n.get("at.help.options.list", None));
-    println!();
-    println!("{}", i18n.get("at.help.examples", None));
-    println!("{}", i18n.get("at.help.examples.list", None));
-} 
-
@@ -0,0 +1,2890 @@
+//! `awk` builtin - pattern scanning and processing language
+//!
+//! Syntax:
+//!   awk [-F FS] [-v VAR=VALUE]... 'PROGRAM' [FILE | VAR=VALUE]...
+//!   awk [-F FS] [-v VAR=VALUE]... -f PROGFILE... [FILE | VAR=VALUE]...
+//!
+//! A program is a list of `PATTERN { ACTION }` rules plus `BEGIN` and `END`
+//! rules and `function` definitions. Each input record (a line, or what `RS`
+//! separates) is split into `$1`..`$NF` on `FS`, and every rule whose
+//! pattern matches runs. Patterns are expressions, `/regex/`s or ranges
+//! `P1, P2`; a rule without an action prints the record.
+//!
+//! The language is POSIX awk without pipes: scalars that are strings and
+//! numbers at once, associative arrays, `if`/`while`/`do`/`for`/`for-in`,
+//! `next`, `exit`, `getline` from the input or `< FILE`, `print` and `printf`
+//! with `>` and `>>` redirections, user functions and the built-in functions
+//! `length substr index split sub gsub match sprintf tolower toupper int
+//! sqrt exp log sin cos atan2 rand srand close fflush`. Regular expressions
+//! are extended ones, translated like `grep -E`'s.
+//!
+//! `for (k in a)` visits keys in the order they were added.
+
+use crate::common::io_message;
+use crate::grep::translate;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The `awk` builtin command implementation
+pub struct AwkCommand;
+
+impl Builtin for AwkCommand {
+    fn name(&self) -> &'static str {
+        "awk"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Pattern scanning and processing language"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run an awk program over the records of the input files or standard input, \
+         splitting each into fields and running the rules whose patterns match."
+    }
+
+    fn usage(&self) -> &'static str {
+        "awk [-F FS] [-v VAR=VALUE]... {'PROGRAM' | -f PROGFILE...} [FILE | VAR=VALUE]..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Pattern scanning and processing language. Use 'awk -F: '{print $1}' /etc/passwd'."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args, &ctx.cwd) {
+            Ok(options) => options,
+            Err(message) => return Ok(failure(format!("awk: {message}\n"))),
+        };
+        let program = match Parser::parse(&options.program) {
+            Ok(program) => program,
+            Err(message) => return Ok(failure(format!("awk: {message}\n"))),
+        };
+        let environment: Vec<(String, String)> = match ctx.env.read() {
+            Ok(env) => env.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        let cwd = ctx.cwd.clone();
+        let mut interp = Interp::new(&program, &mut ctx.stdin, cwd, options.operands);
+        interp.set_array("ENVIRON", environment);
+        if let Some(fs) = options.field_separator {
+            interp.set_var("FS", Value::Str(fs));
+        }
+        for (name, value) in options.assignments {
+            interp.set_var(&name, Value::Strnum(value));
+        }
+        let status = match interp.run() {
+            Ok(()) => interp.exit_code,
+            Err(message) => {
+                interp.stderr.push_str(&format!("awk: {message}\n"));
+                2
+            }
+        };
+        Ok(ExecutionResult::success(status)
+            .with_output(std::mem::take(&mut interp.stdout))
+            .with_error(std::mem::take(&mut interp.stderr).into_bytes()))
+    }
+}
+
+fn failure(message: String) -> ExecutionResult {
+    ExecutionResult::failure(2).with_error(message.into_bytes())
+}
+
+#[derive(Debug)]
+struct Options {
+    program: String,
+    field_separator: Option<String>,
+    assignments: Vec<(String, String)>,
+    operands: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String], cwd: &Path) -> Result<Self, String> {
+        let mut options = Options {
+            program: String::new(),
+            field_separator: None,
+            assignments: Vec::new(),
+            operands: Vec::new(),
+        };
+        let mut files = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                break;
+            }
+            let Some(flag) = arg.strip_prefix('-').and_then(|rest| rest.chars().next()) else {
+                options.operands.push(arg.clone());
+                break;
+            };
+            if !"Fvf".contains(flag) {
+                return Err(format!("invalid option -- '{flag}'"));
+            }
+            let value = match &arg[2..] {
+                "" => iter
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?,
+                rest => rest.to_string(),
+            };
+            match flag {
+                'F' => options.field_separator = Some(unescape(&value)),
+                'v' => options.assignments.push(
+                    assignment(&value).ok_or_else(|| format!("invalid -v argument '{value}'"))?,
+                ),
+                _ => files.push(value),
+            }
+        }
+        options.operands.extend(iter.cloned());
+        if files.is_empty() {
+            if options.operands.is_empty() {
+                return Err(
+                    "usage: awk [-F fs] [-v var=value] ['prog' | -f progfile] [file ...]"
+                        .to_string(),
+                );
+            }
+            options.program = options.operands.remove(0);
+        } else {
+            let mut sources = Vec::new();
+            for file in files {
+                sources.push(
+                    std::fs::read_to_string(cwd.join(&file))
+                        .map_err(|e| format!("can't open file {file}: {}", io_message(&e)))?,
+                );
+            }
+            options.program = sources.join("\n");
+        }
+        Ok(options)
+    }
+}
+
+/// Split `NAME=VALUE` when NAME is a valid variable name, processing
+/// escapes in VALUE
+fn assignment(text: &str) -> Option<(String, String)> {
+    let (name, value) = text.split_once('=')?;
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| (name.to_string(), unescape(value)))
+}
+
+/// Process the escapes of a string literal or command-line value
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0c'),
+            Some('v') => out.push('\x0b'),
+            Some(d @ '0'..='7') => {
+                let mut code = d.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(code).unwrap_or('\0'));
+            }
+            Some(other @ ('"' | '/' | '\\')) => out.push(other),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Values
+
+/// An awk value. Input fields and the like are `Strnum`s, which compare as
+/// numbers when they look like one.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Uninit,
+    Num(f64),
+    Str(String),
+    Strnum(String),
+}
+
+impl Value {
+    fn num(&self) -> f64 {
+        match self {
+            Value::Uninit => 0.0,
+            Value::Num(n) => *n,
+            Value::Str(s) | Value::Strnum(s) => leading_number(s).0,
+        }
+    }
+
+    /// The string form, with CONVFMT for numbers that are not integers
+    fn text(&self, format: &str) -> String {
+        match self {
+            Value::Uninit => String::new(),
+            Value::Num(n) => number_text(*n, format),
+            Value::Str(s) | Value::Strnum(s) => s.clone(),
+        }
+    }
+
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Uninit => false,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::Strnum(s) if looks_numeric(s) => leading_number(s).0 != 0.0,
+            Value::Strnum(s) => !s.is_empty(),
+        }
+    }
+
+    /// Whether comparisons treat the value as a number
+    fn is_numeric(&self) -> bool {
+        match self {
+            Value::Uninit | Value::Num(_) => true,
+            Value::Str(_) => false,
+            Value::Strnum(s) => looks_numeric(s),
+        }
+    }
+}
+
+fn boolean(b: bool) -> Value {
+    Value::Num(if b { 1.0 } else { 0.0 })
+}
+
+/// The number at the start of S, like `strtod`, and how many bytes it took
+fn leading_number(s: &str) -> (f64, usize) {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    let start = i;
+    if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+        i += 1;
+    }
+    let mut digits = 0;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        i += 1;
+        digits += 1;
+    }
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+            digits += 1;
+        }
+    }
+    if digits == 0 {
+        return (0.0, 0);
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j].is_ascii_digit() {
+            while j < bytes.len() && bytes[j].is_ascii_digit() {
+                j += 1;
+            }
+            i = j;
+        }
+    }
+    (s[start..i].parse().unwrap_or(0.0), i)
+}
+
+/// Whether all of S, apart from surrounding blanks, is a number
+fn looks_numeric(s: &str) -> bool {
+    let (_, end) = leading_number(s);
+    end > 0 && s[end..].trim().is_empty()
+}
+
+/// A number as awk prints it: integers plainly, others through FORMAT
+fn number_text(n: f64, format: &str) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e16 {
+        format!("{}", n as i64)
+    } else if n.is_nan() {
+        "nan".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        sprintf(format, &[Value::Num(n)], format)
+    }
+}
+
+/// Format ARGS like C's printf
+fn sprintf(format: &str, args: &[Value], convfmt: &str) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let mut number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                return Some(args.next().map_or(0.0, Value::num) as i64);
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+            digits.parse().ok()
+        };
+        if let Some(width) = number(&mut chars) {
+            if width < 0 {
+                spec.left = true;
+            }
+            spec.width = width.unsigned_abs() as usize;
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(number(&mut chars).unwrap_or(0).max(0) as usize);
+        }
+        let Some(conversion) = chars.next() else {
+            out.push('%');
+            break;
+        };
+        if conversion == '%' {
+            out.push('%');
+            continue;
+        }
+        let arg = args.next().cloned().unwrap_or(Value::Uninit);
+        let body = match conversion {
+            'c' => match &arg {
+                Value::Num(n) => char::from_u32(*n as u32)
+                    .map(String::from)
+                    .unwrap_or_default(),
+                other => other.text(convfmt).chars().take(1).collect(),
+            },
+            's' => {
+                let text = arg.text(convfmt);
+                match spec.precision {
+                    Some(p) => text.chars().take(p).collect(),
+                    None => text,
+                }
+            }
+            'd' | 'i' => spec.integer(arg.num(), 10, false),
+            'o' => spec.integer(arg.num(), 8, false),
+            'x' => spec.integer(arg.num(), 16, false),
+            'X' => spec.integer(arg.num(), 16, true),
+            'u' => spec.integer(arg.num().abs(), 10, false),
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => spec.float(arg.num(), conversion),
+            other => {
+                out.push('%');
+                out.push(other);
+                continue;
+            }
+        };
+        let numeric = !matches!(conversion, 'c' | 's');
+        out.push_str(&spec.pad(body, numeric));
+    }
+    out
+}
+
+/// Format the number N with a printf FORMAT, for builtins such as `seq -f`
+pub(crate) fn format_number(format: &str, n: f64) -> String {
+    sprintf(format, &[Value::Num(n)], "%.6g")
+}
+
+/// The flags, width and precision of one printf conversion, shared with the
+/// `printf` builtin
+#[derive(Debug, Default)]
+pub(crate) struct Spec {
+    pub(crate) left: bool,
+    pub(crate) plus: bool,
+    pub(crate) space: bool,
+    pub(crate) alternate: bool,
+    pub(crate) zero: bool,
+    pub(crate) width: usize,
+    pub(crate) precision: Option<usize>,
+}
+
+impl Spec {
+    fn sign(&self, negative: bool) -> &'static str {
+        match (negative, self.plus, self.space) {
+            (true, _, _) => "-",
+            (false, true, _) => "+",
+            (false, false, true) => " ",
+            _ => "",
+        }
+    }
+
+    fn integer(&mut self, n: f64, radix: u32, upper: bool) -> String {
+        let value = n.trunc();
+        let magnitude = value.abs().min(u64::MAX as f64) as u64;
+        self.whole(value < 0.0, magnitude, radix, upper)
+    }
+
+    /// An integer conversion of the number with MAGNITUDE, negative or not;
+    /// the sign is only shown in base 10
+    pub(crate) fn whole(
+        &mut self,
+        negative: bool,
+        magnitude: u64,
+        radix: u32,
+        upper: bool,
+    ) -> String {
+        let mut digits = match radix {
+            8 => format!("{magnitude:o}"),
+            16 if upper => format!("{magnitude:X}"),
+            16 => format!("{magnitude:x}"),
+            _ => magnitude.to_string(),
+        };
+        if let Some(precision) = self.precision {
+            if precision == 0 && magnitude == 0 {
+                digits.clear();
+            }
+            while digits.len() < precision {
+                digits.insert(0, '0');
+            }
+            // The precision replaces zero padding
+            self.zero = false;
+        }
+        let prefix = match radix {
+            8 if self.alternate && !digits.starts_with('0') => "0",
+            16 if self.alternate && magnitude != 0 => {
+                if upper {
+                    "0X"
+                } else {
+                    "0x"
+                }
+            }
+            _ => "",
+        };
+        let sign = if radix == 10 { self.sign(negative) } else { "" };
+        format!("{sign}{prefix}\0{digits}")
+    }
+
+    pub(crate) fn float(&self, n: f64, conversion: char) -> String {
+        let upper = conversion.is_ascii_uppercase();
+        let sign = self.sign(n < 0.0);
+        let n = n.abs();
+        if !n.is_finite() {
+            let text = if n.is_nan() { "nan" } else { "inf" };
+            return format!(
+                "{sign}\0{}",
+                if upper {
+                    text.to_uppercase()
+                } else {
+                    text.to_string()
+                }
+            );
+        }
+        let precision = self.precision.unwrap_or(6);
+        let text = match conversion.to_ascii_lowercase() {
+            'f' => format!("{n:.precision$}"),
+            'e' => exponential(n, precision),
+            _ => {
+                let precision = precision.max(1);
+                let exponent = if n == 0.0 {
+                    0
+                } else {
+                    exponential(n, precision - 1)
+                        .rsplit_once('e')
+                        .and_then(|(_, e)| e.parse::<i32>().ok())
+                        .unwrap_or(0)
+                };
+                let text = if exponent < -4 || exponent >= precision as i32 {
+                    exponential(n, precision - 1)
+                } else {
+                    let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+                    format!("{n:.decimals$}")
+                };
+                if self.alternate {
+                    text
+                } else {
+                    strip_zeros(&text)
+                }
+            }
+        };
+        let text = if upper { text.to_uppercase() } else { text };
+        format!("{sign}\0{text}")
+    }
+
+    /// Pad a formatted conversion to the width; numbers mark with a NUL
+    /// where zero padding goes, after their sign and prefix
+    pub(crate) fn pad(&self, body: String, numeric: bool) -> String {
+        let (head, tail) = match body.split_once('\0') {
+            Some((head, tail)) if numeric => (head.to_string(), tail.to_string()),
+            _ => (String::new(), body),
+        };
+        let length = head.chars().count() + tail.chars().count();
+        if length >= self.width {
+            return format!("{head}{tail}");
+        }
+        let fill = self.width - length;
+        if self.left {
+            format!("{head}{tail}{}", " ".repeat(fill))
+        } else if self.zero && numeric {
+            format!("{head}{}{tail}", "0".repeat(fill))
+        } else {
+            format!("{}{head}{tail}", " ".repeat(fill))
+        }
+    }
+}
+
+/// `%e` formatting with C's two-digit signed exponent
+fn exponential(n: f64, precision: usize) -> String {
+    let text = format!("{n:.precision$e}");
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}e{sign}{:02}", exponent.abs())
+}
+
+/// Drop trailing zeros after a decimal point, as `%g` does
+fn strip_zeros(text: &str) -> String {
+    let (mantissa, exponent) = match text.find('e') {
+        Some(at) => text.split_at(at),
+        None => (text, ""),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{mantissa}{exponent}")
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+    /// A name followed directly by `(`
+    FuncName(String),
+    Builtin(&'static str),
+    Keyword(&'static str),
+    Sym(&'static str),
+    Newline,
+    Eof,
+}
+
+const KEYWORDS: &[&str] = &[
+    "BEGIN", "END", "function", "func", "if", "else", "while", "for", "do", "break", "continue",
+    "next", "exit", "return", "delete", "in", "getline", "print", "printf",
+];
+
+const BUILTINS: &[&str] = &[
+    "length", "substr", "index", "split", "sub", "gsub", "match", "sprintf", "sin", "cos", "atan2",
+    "exp", "log", "sqrt", "int", "rand", "srand", "tolower", "toupper", "close", "fflush",
+];
+
+const SYMBOLS: &[&str] = &[
+    "**=", "+=", "-=", "*=", "/=", "%=", "^=", "==", "<=", ">=", "!=", "++", "--", "&&", "||",
+    ">>", "!~", "**", "{", "}", "(", ")", "[", "]", ";", ",", "+", "-", "*", "/", "%", "^", "!",
+    ">", "<", "|", "?", ":", "~", "$", "=",
+];
+
+/// Split a program into tokens, each with its line number
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        // A `/` after an operand divides; anywhere else it starts a regex
+        let divides = matches!(
+            tokens.last().map(|(token, _)| token),
+            Some(Token::Number(_))
+                | Some(Token::Str(_))
+                | Some(Token::Name(_))
+                | Some(Token::Builtin(_))
+                | Some(Token::Sym(")"))
+                | Some(Token::Sym("]"))
+                | Some(Token::Sym("$"))
+                | Some(Token::Sym("++"))
+                | Some(Token::Sym("--"))
+        );
+        match c {
+            ' ' | '\t' | '\r' => i += 1,
+            '\\' if chars.get(i + 1) == Some(&'\n') => {
+                i += 2;
+                line += 1;
+            }
+            '\\' if chars.get(i + 1) == Some(&'\r') && chars.get(i + 2) == Some(&'\n') => {
+                i += 3;
+                line += 1;
+            }
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+                i += 1;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '"' => {
+                let mut raw = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(format!("line {line}: unterminated string"))
+                        }
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            raw.push('\\');
+                            raw.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        Some(&c) => raw.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Str(unescape(&raw)), line));
+            }
+            '/' if !divides => {
+                let mut raw = String::new();
+                let mut in_bracket = false;
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(format!("line {line}: unterminated regexp"))
+                        }
+                        Some('/') if !in_bracket => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            raw.push('/');
+                            i += 1;
+                        }
+                        Some('\\') if i + 1 < chars.len() => {
+                            raw.push('\\');
+                            raw.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        Some('[') if !in_bracket => {
+                            in_bracket = true;
+                            raw.push('[');
+                            if chars.get(i + 1) == Some(&'^') {
+                                raw.push('^');
+                                i += 1;
+                            }
+                            if chars.get(i + 1) == Some(&']') {
+                                raw.push(']');
+                                i += 1;
+                            }
+                        }
+                        Some(']') if in_bracket => {
+                            in_bracket = false;
+                            raw.push(']');
+                        }
+                        Some(&c) => raw.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Regex(raw), line));
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let rest: String = chars[i..].iter().collect();
+                let (value, length) = leading_number(&rest);
+                tokens.push((Token::Number(value), line));
+                i += rest[..length].chars().count();
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = if let Some(keyword) = KEYWORDS.iter().find(|k| **k == word) {
+                    Token::Keyword(keyword)
+                } else if let Some(builtin) = BUILTINS.iter().find(|b| **b == word) {
+                    Token::Builtin(builtin)
+                } else if chars.get(i) == Some(&'(') {
+                    Token::FuncName(word)
+                } else {
+                    Token::Name(word)
+                };
+                tokens.push((token, line));
+            }
+            _ => {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| {
+                        symbol
+                            .chars()
+                            .enumerate()
+                            .all(|(k, s)| chars.get(i + k) == Some(&s))
+                    })
+                    .ok_or_else(|| format!("line {line}: invalid char '{c}' in expression"))?;
+                i += symbol.len();
+                tokens.push((Token::Sym(symbol), line));
+            }
+        }
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+// ---------------------------------------------------------------------------
+// Syntax tree
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(f64),
+    Str(String),
+    /// A regex literal; on its own it matches `$0`
+    Regex(String),
+    Var(String),
+    Field(Box<Expr>),
+    Index(String, Vec<Expr>),
+    Assign(Box<Expr>, Option<Arith>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Plus(Box<Expr>),
+    Binary(Arith, Box<Expr>, Box<Expr>),
+    Compare(Ordering, bool, Box<Expr>, Box<Expr>),
+    Match(bool, Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    In(Vec<Expr>, String),
+    Incr {
+        target: Box<Expr>,
+        delta: f64,
+        prefix: bool,
+    },
+    Call(String, Vec<Expr>),
+    Builtin(&'static str, Vec<Expr>),
+    Getline {
+        target: Option<Box<Expr>>,
+        file: Option<Box<Expr>>,
+    },
+    /// `(a, b)`, only valid as the arguments of `print` or before `in`
+    Group(Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Redirect {
+    append: bool,
+    target: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Expr(Expr),
+    Print(Vec<Expr>, Option<Redirect>),
+    Printf(Vec<Expr>, Option<Redirect>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Do(Box<Stmt>, Expr),
+    For(
+        Option<Box<Stmt>>,
+        Option<Expr>,
+        Option<Box<Stmt>>,
+        Box<Stmt>,
+    ),
+    ForIn(String, String, Box<Stmt>),
+    Block(Vec<Stmt>),
+    Next,
+    Exit(Option<Expr>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Delete(String, Option<Vec<Expr>>),
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Begin,
+    End,
+    All,
+    Expr(Expr),
+    Range(Expr, Expr),
+}
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Pattern,
+    /// `None` prints the record
+    action: Option<Vec<Stmt>>,
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+struct Program {
+    rules: Vec<Rule>,
+    functions: HashMap<String, Function>,
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse(source: &str) -> Result<Program, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let mut program = Program {
+            rules: Vec::new(),
+            functions: HashMap::new(),
+        };
+        loop {
+            parser.skip_terminators();
+            match parser.peek().clone() {
+                Token::Eof => break,
+                Token::Keyword("function") | Token::Keyword("func") => {
+                    parser.pos += 1;
+                    let name = match parser.next() {
+                        Token::Name(name) | Token::FuncName(name) => name,
+                        _ => return Err(parser.error("function name expected")),
+                    };
+                    parser.expect("(")?;
+                    let mut params = Vec::new();
+                    while !parser.at(")") {
+                        match parser.next() {
+                            Token::Name(param) => params.push(param),
+                            _ => return Err(parser.error("parameter name expected")),
+                        }
+                        if parser.eat(",") {
+                            parser.skip_newlines();
+                        }
+                    }
+                    parser.expect(")")?;
+                    parser.skip_newlines();
+                    let body = parser.block()?;
+                    program.functions.insert(name, Function { params, body });
+                }
+                Token::Keyword(which @ ("BEGIN" | "END")) => {
+                    parser.pos += 1;
+                    parser.skip_newlines();
+                    let action = parser.block()?;
+                    program.rules.push(Rule {
+                        pattern: if which == "BEGIN" {
+                            Pattern::Begin
+                        } else {
+                            Pattern::End
+                        },
+                        action: Some(action),
+                    });
+                }
+                Token::Sym("{") => {
+                    let action = parser.block()?;
+                    program.rules.push(Rule {
+                        pattern: Pattern::All,
+                        action: Some(action),
+                    });
+                }
+                _ => {
+                    let first = parser.expr(false)?;
+                    let pattern = if parser.eat(",") {
+                        parser.skip_newlines();
+                        Pattern::Range(first, parser.expr(false)?)
+                    } else {
+                        Pattern::Expr(first)
+                    };
+                    let action = if parser.at("{") {
+                        Some(parser.block()?)
+                    } else {
+                        None
+                    };
+                    program.rules.push(Rule { pattern, action });
+                }
+            }
+        }
+        Ok(program)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn at(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Token::Sym(s) if *s == symbol)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Keyword(k) if *k == keyword)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.at(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{symbol}'")))
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        let (token, line) = &self.tokens[self.pos];
+        let near = match token {
+            Token::Number(n) => number_text(*n, "%.6g"),
+            Token::Str(s) => format!("\"{s}\""),
+            Token::Regex(r) => format!("/{r}/"),
+            Token::Name(n) | Token::FuncName(n) => n.clone(),
+            Token::Builtin(s) | Token::Keyword(s) | Token::Sym(s) => s.to_string(),
+            Token::Newline => "end of line".to_string(),
+            Token::Eof => "end of program".to_string(),
+        };
+        format!("syntax error at line {line} near {near}: {message}")
+    }
+
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Token::Newline {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_terminators(&mut self) {
+        while *self.peek() == Token::Newline || self.at(";") {
+            self.pos += 1;
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        loop {
+            self.skip_terminators();
+            if self.eat("}") {
+                return Ok(statements);
+            }
+            if *self.peek() == Token::Eof {
+                return Err(self.error("unexpected end of program, missing `}'"));
+            }
+            statements.push(self.statement()?);
+        }
+    }
+
+    /// A statement that ends at `;`, a newline, `}` or the end
+    fn end_simple(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Token::Newline => {
+                self.pos += 1;
+                Ok(())
+            }
+            Token::Sym(";") => {
+                self.pos += 1;
+                Ok(())
+            }
+            Token::Sym("}") | Token::Eof => Ok(()),
+            _ => Err(self.error("unexpected token")),
+        }
+    }
+
+    /// The body of `if`, `while` or `for`, which may be an empty `;`
+    fn body(&mut self) -> Result<Stmt, String> {
+        self.skip_newlines();
+        if self.eat(";") {
+            return Ok(Stmt::Block(Vec::new()));
+        }
+        self.statement()
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        let statement = match self.peek().clone() {
+            Token::Sym("{") => return Ok(Stmt::Block(self.block()?)),
+            Token::Keyword("if") => {
+                self.pos += 1;
+                self.expect("(")?;
+                let condition = self.expr(false)?;
+                self.expect(")")?;
+                let then = self.body()?;
+                let save = self.pos;
+                self.skip_terminators();
+                let otherwise = if self.at_keyword("else") {
+                    self.pos += 1;
+                    Some(Box::new(self.body()?))
+                } else {
+                    self.pos = save;
+                    None
+                };
+                return Ok(Stmt::If(condition, Box::new(then), otherwise));
+            }
+            Token::Keyword("while") => {
+                self.pos += 1;
+                self.expect("(")?;
+                let condition = self.expr(false)?;
+                self.expect(")")?;
+                return Ok(Stmt::While(condition, Box::new(self.body()?)));
+            }
+            Token::Keyword("do") => {
+                self.pos += 1;
+                let body = self.body()?;
+                self.skip_terminators();
+                if !self.at_keyword("while") {
+                    return Err(self.error("expected `while' after `do' body"));
+                }
+                self.pos += 1;
+                self.expect("(")?;
+                let condition = self.expr(false)?;
+                self.expect(")")?;
+                Stmt::Do(Box::new(body), condition)
+            }
+            Token::Keyword("for") => {
+                self.pos += 1;
+                self.expect("(")?;
+                let ahead = |k: usize| {
+                    self.tokens
+                        .get(self.pos + k)
+                        .map_or(Token::Eof, |t| t.0.clone())
+                };
+                if let (
+                    Token::Name(key),
+                    Token::Keyword("in"),
+                    Token::Name(array),
+                    Token::Sym(")"),
+                ) = (ahead(0), ahead(1), ahead(2), ahead(3))
+                {
+                    self.pos += 4;
+                    return Ok(Stmt::ForIn(key, array, Box::new(self.body()?)));
+                }
+                let init = if self.at(";") {
+                    None
+                } else {
+                    Some(Box::new(self.simple_statement()?))
+                };
+                self.expect(";")?;
+                self.skip_newlines();
+                let condition = if self.at(";") {
+                    None
+                } else {
+                    Some(self.expr(false)?)
+                };
+                self.expect(";")?;
+                self.skip_newlines();
+                let step = if self.at(")") {
+                    None
+                } else {
+                    Some(Box::new(self.simple_statement()?))
+                };
+                self.expect(")")?;
+                return Ok(Stmt::For(init, condition, step, Box::new(self.body()?)));
+            }
+            _ => self.simple_statement()?,
+        };
+        self.end_simple()?;
+        Ok(statement)
+    }
+
+    fn simple_statement(&mut self) -> Result<Stmt, String> {
+        Ok(match self.peek().clone() {
+            Token::Keyword(which @ ("print" | "printf")) => {
+                self.pos += 1;
+                let mut args = if matches!(
+                    self.peek(),
+                    Token::Newline | Token::Eof | Token::Sym(";") | Token::Sym("}")
+                ) || self.at(">")
+                    || self.at(">>")
+                    || self.at("|")
+                {
+                    Vec::new()
+                } else {
+                    self.expr_list(true)?
+                };
+                if let [Expr::Group(items)] = args.as_slice() {
+                    args = items.clone();
+                }
+                let redirect = if self.at("|") {
+                    return Err(self.error("output pipes are not supported"));
+                } else if self.eat(">>") {
+                    Some(Redirect {
+                        append: true,
+                        target: self.concat()?,
+                    })
+                } else if self.eat(">") {
+                    Some(Redirect {
+                        append: false,
+                        target: self.concat()?,
+                    })
+                } else {
+                    None
+                };
+                if which == "print" {
+                    Stmt::Print(args, redirect)
+                } else {
+                    if args.is_empty() {
+                        return Err(self.error("printf: no format"));
+                    }
+                    Stmt::Printf(args, redirect)
+                }
+            }
+            Token::Keyword("next") => {
+                self.pos += 1;
+                Stmt::Next
+            }
+            Token::Keyword("break") => {
+                self.pos += 1;
+                Stmt::Break
+            }
+            Token::Keyword("continue") => {
+                self.pos += 1;
+                Stmt::Continue
+            }
+            Token::Keyword(which @ ("exit" | "return")) => {
+                self.pos += 1;
+                let value = if matches!(
+                    self.peek(),
+                    Token::Newline | Token::Eof | Token::Sym(";") | Token::Sym("}")
+                ) {
+                    None
+                } else {
+                    Some(self.expr(false)?)
+                };
+                if which == "exit" {
+                    Stmt::Exit(value)
+                } else {
+                    Stmt::Return(value)
+                }
+            }
+            Token::Keyword("delete") => {
+                self.pos += 1;
+                let Token::Name(name) = self.next() else {
+                    return Err(self.error("array name expected after delete"));
+                };
+                let subscripts = if self.eat("[") {
+                    let subscripts = self.expr_list(false)?;
+                    self.expect("]")?;
+                    Some(subscripts)
+                } else {
+                    None
+                };
+                Stmt::Delete(name, subscripts)
+            }
+            _ => Stmt::Expr(self.expr(false)?),
+        })
+    }
+
+    fn expr_list(&mut self, no_gt: bool) -> Result<Vec<Expr>, String> {
+        let mut list = vec![self.expr(no_gt)?];
+        while self.eat(",") {
+            self.skip_newlines();
+            list.push(self.expr(no_gt)?);
+        }
+        Ok(list)
+    }
+
+    /// An expression; with NO_GT a bare `>` is left for a print redirection
+    fn expr(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let left = self.or(no_gt)?;
+        let op = match self.peek() {
+            Token::Sym("=") => None,
+            Token::Sym("+=") => Some(Arith::Add),
+            Token::Sym("-=") => Some(Arith::Sub),
+            Token::Sym("*=") => Some(Arith::Mul),
+            Token::Sym("/=") => Some(Arith::Div),
+            Token::Sym("%=") => Some(Arith::Mod),
+            Token::Sym("^=") | Token::Sym("**=") => Some(Arith::Pow),
+            Token::Sym("?") => {
+                self.pos += 1;
+                self.skip_newlines();
+                let then = self.expr(no_gt)?;
+                self.skip_newlines();
+                self.expect(":")?;
+                self.skip_newlines();
+                let otherwise = self.expr(no_gt)?;
+                return Ok(Expr::Cond(
+                    Box::new(left),
+                    Box::new(then),
+                    Box::new(otherwise),
+                ));
+            }
+            _ => return Ok(left),
+        };
+        if !is_lvalue(&left) {
+            return Err(self.error("assignment to a value that is not a variable"));
+        }
+        self.pos += 1;
+        self.skip_newlines();
+        let right = self.expr(no_gt)?;
+        Ok(Expr::Assign(Box::new(left), op, Box::new(right)))
+    }
+
+    fn or(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let mut left = self.and(no_gt)?;
+        while self.eat("||") {
+            self.skip_newlines();
+            left = Expr::Or(Box::new(left), Box::new(self.and(no_gt)?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let mut left = self.membership(no_gt)?;
+        while self.eat("&&") {
+            self.skip_newlines();
+            left = Expr::And(Box::new(left), Box::new(self.membership(no_gt)?));
+        }
+        Ok(left)
+    }
+
+    fn membership(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let mut left = self.matching(no_gt)?;
+        while self.at_keyword("in") {
+            self.pos += 1;
+            let Token::Name(array) = self.next() else {
+                return Err(self.error("array name expected after `in'"));
+            };
+            let keys = match left {
+                Expr::Group(keys) => keys,
+                other => vec![other],
+            };
+            left = Expr::In(keys, array);
+        }
+        Ok(left)
+    }
+
+    fn matching(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let mut left = self.comparison(no_gt)?;
+        loop {
+            let negate = if self.eat("~") {
+                false
+            } else if self.eat("!~") {
+                true
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Match(negate, Box::new(left), Box::new(self.comparison(no_gt)?));
+        }
+    }
+
+    fn comparison(&mut self, no_gt: bool) -> Result<Expr, String> {
+        let left = self.concat()?;
+        let (ordering, equal) = match self.peek() {
+            Token::Sym("<") => (Ordering::Less, false),
+            Token::Sym("<=") => (Ordering::Less, true),
+            Token::Sym("==") => (Ordering::Equal, true),
+            Token::Sym("!=") => (Ordering::Equal, false),
+            Token::Sym(">=") => (Ordering::Greater, true),
+            Token::Sym(">") if !no_gt => (Ordering::Greater, false),
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.concat()?;
+        Ok(Expr::Compare(
+            ordering,
+            equal,
+            Box::new(left),
+            Box::new(right),
+        ))
+    }
+
+    fn concat(&mut self) -> Result<Expr, String> {
+        let mut left = self.additive()?;
+        while matches!(
+            self.peek(),
+            Token::Number(_)
+                | Token::Str(_)
+                | Token::Regex(_)
+                | Token::Name(_)
+                | Token::FuncName(_)
+                | Token::Builtin(_)
+                | Token::Sym("$")
+                | Token::Sym("(")
+                | Token::Sym("++")
+                | Token::Sym("--")
+        ) {
+            left = Expr::Concat(Box::new(left), Box::new(self.additive()?));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Sym("+") => Arith::Add,
+                Token::Sym("-") => Arith::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Sym("*") => Arith::Mul,
+                Token::Sym("/") => Arith::Div,
+                Token::Sym("%") => Arith::Mod,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return Ok(Expr::Plus(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.postfix()?;
+        if self.eat("^") || self.eat("**") {
+            // Right associative, and `2^-1` is allowed
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(Arith::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let expr = self.primary()?;
+        if is_lvalue(&expr) {
+            for (symbol, delta) in [("++", 1.0), ("--", -1.0)] {
+                if self.eat(symbol) {
+                    return Ok(Expr::Incr {
+                        target: Box::new(expr),
+                        delta,
+                        prefix: false,
+                    });
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Token::Number(n) => Ok(Expr::Num(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Regex(r) => Ok(Expr::Regex(r)),
+            Token::Sym("$") => {
+                let index = if self.at("++") || self.at("--") {
+                    self.primary()?
+                } else if self.eat("-") {
+                    Expr::Neg(Box::new(self.primary()?))
+                } else {
+                    self.primary()?
+                };
+                Ok(Expr::Field(Box::new(index)))
+            }
+            Token::Sym(symbol @ ("++" | "--")) => {
+                let target = self.primary()?;
+                if !is_lvalue(&target) {
+                    return Err(self.error(&format!("`{symbol}' needs a variable")));
+                }
+                Ok(Expr::Incr {
+                    target: Box::new(target),
+                    delta: if symbol == "++" { 1.0 } else { -1.0 },
+                    prefix: true,
+                })
+            }
+            Token::Sym("(") => {
+                self.skip_newlines();
+                let mut items = vec![self.expr(false)?];
+                while self.eat(",") {
+                    self.skip_newlines();
+                    items.push(self.expr(false)?);
+                }
+                self.skip_newlines();
+                self.expect(")")?;
+                Ok(if items.len() == 1 {
+                    items.pop().unwrap_or(Expr::Num(0.0))
+                } else {
+                    Expr::Group(items)
+                })
+            }
+            Token::Name(name) => {
+                if self.eat("[") {
+                    let subscripts = self.expr_list(false)?;
+                    self.expect("]")?;
+                    Ok(Expr::Index(name, subscripts))
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            Token::FuncName(name) => {
+                self.expect("(")?;
+                let args = self.call_args()?;
+                Ok(Expr::Call(name, args))
+            }
+            Token::Builtin(name) => {
+                let args = if self.eat("(") {
+                    self.call_args()?
+                } else if name == "length" {
+                    Vec::new()
+                } else {
+                    return Err(self.error(&format!("`{name}' needs arguments")));
+                };
+                Ok(Expr::Builtin(name, args))
+            }
+            Token::Keyword("getline") => {
+                let target = if matches!(self.peek(), Token::Name(_) | Token::Sym("$")) {
+                    Some(Box::new(self.primary()?))
+                } else {
+                    None
+                };
+                let file = if self.eat("<") {
+                    Some(Box::new(self.primary()?))
+                } else {
+                    None
+                };
+                Ok(Expr::Getline { target, file })
+            }
+            _ => {
+                self.pos = self.pos.saturating_sub(1);
+                Err(self.error("unexpected token"))
+            }
+        }
+    }
+
+    /// Arguments up to the `)` of a call whose `(` was consumed
+    fn call_args(&mut self) -> Result<Vec<Expr>, String> {
+        self.skip_newlines();
+        if self.eat(")") {
+            return Ok(Vec::new());
+        }
+        let args = self.expr_list(false)?;
+        self.skip_newlines();
+        self.expect(")")?;
+        Ok(args)
+    }
+}
+
+fn is_lvalue(expr: &Expr) -> bool {
+    matches!(expr, Expr::Var(_) | Expr::Field(_) | Expr::Index(..))
+}
+
+// ---------------------------------------------------------------------------
+// Interpreter
+
+/// An associative array, iterated in insertion order
+#[derive(Debug, Default)]
+struct Array {
+    values: HashMap<String, Value>,
+    keys: Vec<String>,
+}
+
+impl Array {
+    fn insert(&mut self, key: String, value: Value) {
+        if self.values.insert(key.clone(), value).is_none() {
+            self.keys.push(key);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if self.values.remove(key).is_some() {
+            self.keys.retain(|k| k != key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.keys.clear();
+    }
+}
+
+/// A variable: a scalar, or a reference to an array in the arena
+#[derive(Debug, Clone)]
+enum Cell {
+    Scalar(Value),
+    Array(usize),
+}
+
+/// Why evaluation stopped early
+#[derive(Debug)]
+enum Stop {
+    Error(String),
+    Next,
+    Exit,
+}
+
+impl From<String> for Stop {
+    fn from(message: String) -> Self {
+        Stop::Error(message)
+    }
+}
+
+/// How a statement finished
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Records read from one input
+struct Source {
+    data: String,
+    pos: usize,
+}
+
+impl Source {
+    fn new(data: Vec<u8>) -> Self {
+        Source {
+            data: String::from_utf8_lossy(&data).into_owned(),
+            pos: 0,
+        }
+    }
+
+    /// The next record separated by RS: a newline by default, blank lines
+    /// when RS is empty, and a regular expression when it is longer than a
+    /// character
+    fn next(&mut self, separator: &str, regex: Option<&Regex>) -> Option<String> {
+        let rest = &self.data[self.pos..];
+        if separator.is_empty() {
+            let trimmed = rest.trim_start_matches('\n');
+            if trimmed.is_empty() {
+                self.pos = self.data.len();
+                return None;
+            }
+            let start = self.pos + rest.len() - trimmed.len();
+            let (record, end) = match trimmed.find("\n\n") {
+                Some(at) => {
+                    let after = &trimmed[at..];
+                    let blank = after.len() - after.trim_start_matches('\n').len();
+                    (&trimmed[..at], start + at + blank)
+                }
+                None => (trimmed.trim_end_matches('\n'), self.data.len()),
+            };
+            let record = record.to_string();
+            self.pos = end;
+            return Some(record);
+        }
+        if rest.is_empty() {
+            return None;
+        }
+        let found = match regex {
+            Some(regex) => regex.find(rest).map(|m| (m.start(), m.end())),
+            None => rest.find(separator).map(|at| (at, at + separator.len())),
+        };
+        let (record, advance) = match found {
+            Some((start, end)) => (&rest[..start], end),
+            None => (rest, rest.len()),
+        };
+        let record = record.to_string();
+        self.pos += advance;
+        Some(record)
+    }
+}
+
+struct Interp<'a> {
+    program: &'a Program,
+    globals: HashMap<String, Cell>,
+    frames: Vec<HashMap<String, Cell>>,
+    arrays: Vec<Array>,
+    record: String,
+    fields: Vec<String>,
+    regexes: HashMap<String, Regex>,
+    stdout: Vec<u8>,
+    stderr: String,
+    outputs: HashMap<String, std::fs::File>,
+    inputs: HashMap<String, Source>,
+    stdin: &'a mut dyn Read,
+    cwd: PathBuf,
+    operands: Vec<String>,
+    next_operand: usize,
+    main: Option<Source>,
+    read_any: bool,
+    ranges: Vec<bool>,
+    exit_code: i32,
+    seed: f64,
+    random: u64,
+}
+
+const MAX_CALL_DEPTH: usize = 1000;
+
+impl<'a> Interp<'a> {
+    fn new(
+        program: &'a Program,
+        stdin: &'a mut dyn Read,
+        cwd: PathBuf,
+        operands: Vec<String>,
+    ) -> Self {
+        let mut interp = Interp {
+            program,
+            globals: HashMap::new(),
+            frames: Vec::new(),
+            arrays: Vec::new(),
+            record: String::new(),
+            fields: Vec::new(),
+            regexes: HashMap::new(),
+            stdout: Vec::new(),
+            stderr: String::new(),
+            outputs: HashMap::new(),
+            inputs: HashMap::new(),
+            stdin,
+            cwd,
+            operands: operands.clone(),
+            next_operand: 0,
+            main: None,
+            read_any: false,
+            ranges: vec![false; program.rules.len()],
+            exit_code: 0,
+            seed: 0.0,
+            random: 0x2545_f491_4f6c_dd1d,
+        };
+        for (name, value) in [
+            ("FS", " "),
+            ("OFS", " "),
+            ("ORS", "\n"),
+            ("RS", "\n"),
+            ("SUBSEP", "\x1c"),
+            ("CONVFMT", "%.6g"),
+            ("OFMT", "%.6g"),
+            ("FILENAME", ""),
+        ] {
+            interp.set_var(name, Value::Str(value.to_string()));
+        }
+        for name in ["NR", "FNR", "RSTART", "RLENGTH"] {
+            interp.set_var(name, Value::Num(0.0));
+        }
+        interp.set_var("ARGC", Value::Num(operands.len() as f64 + 1.0));
+        let argv = std::iter::once("awk".to_string())
+            .chain(operands)
+            .enumerate()
+            .map(|(i, arg)| (i.to_string(), arg))
+            .collect();
+        interp.set_array("ARGV", argv);
+        interp
+    }
+
+    fn set_array(&mut self, name: &str, entries: Vec<(String, String)>) {
+        let mut array = Array::default();
+        for (key, value) in entries {
+            array.insert(key, Value::Strnum(value));
+        }
+        self.arrays.push(array);
+        self.globals
+            .insert(name.to_string(), Cell::Array(self.arrays.len() - 1));
+    }
+
+    fn special(&self, name: &str) -> String {
+        match self.globals.get(name) {
+            Some(Cell::Scalar(value)) => value.text("%.6g"),
+            _ => String::new(),
+        }
+    }
+
+    fn convfmt(&self) -> String {
+        self.special("CONVFMT")
+    }
+
+    fn text(&self, value: &Value) -> String {
+        match value {
+            Value::Num(_) => value.text(&self.convfmt()),
+            other => other.text(""),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        let program = self.program;
+        let mut exited = false;
+        for rule in &program.rules {
+            if let (Pattern::Begin, Some(action)) = (&rule.pattern, &rule.action) {
+                if self.run_action(action)? {
+                    exited = true;
+                    break;
+                }
+            }
+        }
+        let needs_input = program
+            .rules
+            .iter()
+            .any(|rule| !matches!(rule.pattern, Pattern::Begin));
+        if !exited && needs_input {
+            'records: while let Some(record) = self.next_record()? {
+                self.set_record(record);
+                self.bump("NR");
+                self.bump("FNR");
+                for (index, rule) in program.rules.iter().enumerate() {
+                    if !self.rule_matches(index, &rule.pattern)? {
+                        continue;
+                    }
+                    let stop = match &rule.action {
+                        Some(action) => self.run_block(action),
+                        None => {
+                            let line = format!("{}{}", self.record, self.special("ORS"));
+                            self.stdout.extend(line.as_bytes());
+                            Ok(Flow::Normal)
+                        }
+                    };
+                    match stop {
+                        Ok(_) => {}
+                        Err(Stop::Next) => continue 'records,
+                        Err(Stop::Exit) => break 'records,
+                        Err(Stop::Error(message)) => return Err(message),
+                    }
+                }
+            }
+        }
+        for rule in &program.rules {
+            if let (Pattern::End, Some(action)) = (&rule.pattern, &rule.action) {
+                if self.run_action(action)? {
+                    break;
+                }
+            }
+        }
+        for (_, mut file) in self.outputs.drain() {
+            let _ = file.flush();
+        }
+        Ok(())
+    }
+
+    /// Run a BEGIN or END action; true when it called `exit`
+    fn run_action(&mut self, action: &[Stmt]) -> Result<bool, String> {
+        match self.run_block(action) {
+            Ok(_) => Ok(false),
+            Err(Stop::Exit) => Ok(true),
+            Err(Stop::Next) => Err("`next' used in BEGIN or END".to_string()),
+            Err(Stop::Error(message)) => Err(message),
+        }
+    }
+
+    fn bump(&mut self, name: &str) {
+        let value = self.get_var(name).unwrap_or(Value::Uninit).num() + 1.0;
+        self.set_var(name, Value::Num(value));
+    }
+
+    fn rule_matches(&mut self, index: usize, pattern: &Pattern) -> Result<bool, String> {
+        let test = |interp: &mut Self, expr: &Expr| -> Result<bool, String> {
+            match interp.eval(expr) {
+                Ok(value) => Ok(value.is_true()),
+                Err(Stop::Error(message)) => Err(message),
+                Err(_) => Err("`next' or `exit' in a pattern".to_string()),
+            }
+        };
+        Ok(match pattern {
+            Pattern::Begin | Pattern::End => false,
+            Pattern::All => true,
+            Pattern::Expr(expr) => test(self, expr)?,
+            Pattern::Range(start, end) => {
+                if !self.ranges[index] {
+                    if !test(self, start)? {
+                        return Ok(false);
+                    }
+                    self.ranges[index] = true;
+                }
+                if test(self, end)? {
+                    self.ranges[index] = false;
+                }
+                true
+            }
+        })
+    }
+
+    /// The next main input record, moving through the operands; `VAR=VALUE`
+    /// operands are assigned when they are reached
+    fn next_record(&mut self) -> Result<Option<String>, String> {
+        loop {
+            let separator = self.special("RS");
+            let regex = if separator.chars().count() > 1 {
+                Some(self.regex(&separator)?)
+            } else {
+                None
+            };
+            if let Some(source) = &mut self.main {
+                if let Some(record) = source.next(&separator, regex.as_ref()) {
+                    return Ok(Some(record));
+                }
+                self.main = None;
+            }
+            let Some(operand) = self.operands.get(self.next_operand).cloned() else {
+                if self.read_any {
+                    return Ok(None);
+                }
+                self.read_any = true;
+                let mut data = Vec::new();
+                let _ = self.stdin.read_to_end(&mut data);
+                self.main = Some(Source::new(data));
+                continue;
+            };
+            self.next_operand += 1;
+            if let Some((name, value)) = assignment(&operand) {
+                self.set_var(&name, Value::Strnum(value));
+                continue;
+            }
+            if operand.is_empty() {
+                continue;
+            }
+            self.read_any = true;
+            let data = if operand == "-" || operand == "/dev/stdin" {
+                let mut data = Vec::new();
+                let _ = self.stdin.read_to_end(&mut data);
+                data
+            } else {
+                match std::fs::read(self.cwd.join(&operand)) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        self.stderr.push_str(&format!(
+                            "awk: can't open file {operand}: {}\n",
+                            io_message(&e)
+                        ));
+                        self.exit_code = 2;
+                        continue;
+                    }
+                }
+            };
+            self.set_var("FILENAME", Value::Str(operand));
+            self.set_var("FNR", Value::Num(0.0));
+            self.main = Some(Source::new(data));
+        }
+    }
+
+    fn set_record(&mut self, record: String) {
+        self.record = record;
+        self.fields = self.split(&self.record.clone(), None);
+    }
+
+    /// Split TEXT into fields on SEPARATOR, or FS: a single space means runs
+    /// of blanks and newlines, another single character stands for itself,
+    /// and anything longer is a regular expression
+    fn split(&mut self, text: &str, separator: Option<&Expr>) -> Vec<String> {
+        let separator = match separator {
+            Some(Expr::Regex(regex)) => Err(regex.clone()),
+            Some(expr) => Ok(self.eval(expr).map(|v| self.text(&v)).unwrap_or_default()),
+            None => Ok(self.special("FS")),
+        };
+        let pattern = match separator {
+            Ok(fs) if fs == " " => {
+                return text.split_whitespace().map(str::to_string).collect();
+            }
+            _ if text.is_empty() => return Vec::new(),
+            Ok(fs) if fs.is_empty() => return text.chars().map(String::from).collect(),
+            Ok(fs) if fs.chars().count() == 1 && fs != "\\" => {
+                return text.split(fs.as_str()).map(str::to_string).collect();
+            }
+            Ok(fs) | Err(fs) => fs,
+        };
+        match self.regex(&pattern) {
+            Ok(regex) => regex.split(text).map(str::to_string).collect(),
+            Err(_) => vec![text.to_string()],
+        }
+    }
+
+    /// Rebuild `$0` from the fields with OFS
+    fn rebuild(&mut self) {
+        self.record = self.fields.join(&self.special("OFS"));
+    }
+
+    fn regex(&mut self, pattern: &str) -> Result<Regex, String> {
+        if let Some(regex) = self.regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let source = translate(pattern, true).map_err(|e| format!("regex /{pattern}/: {e}"))?;
+        let regex = Regex::new(&format!("(?s){source}")).map_err(|e| match e {
+            regex::Error::Syntax(message) => format!("regex /{pattern}/: {message}"),
+            other => format!("regex /{pattern}/: {other}"),
+        })?;
+        self.regexes.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    /// The regex of a `~` operand or a function argument: a literal is used
+    /// as is, any other value as a dynamic regex
+    fn regex_of(&mut self, expr: &Expr) -> Result<Regex, Stop> {
+        let pattern = match expr {
+            Expr::Regex(pattern) => pattern.clone(),
+            other => {
+                let value = self.eval(other)?;
+                self.text(&value)
+            }
+        };
+        Ok(self.regex(&pattern)?)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Cell> {
+        match self.frames.last().and_then(|frame| frame.get(name)) {
+            Some(cell) => Some(cell),
+            None => self.globals.get(name),
+        }
+    }
+
+    fn get_var(&self, name: &str) -> Result<Value, String> {
+        if name == "NF" && !self.frames.last().is_some_and(|f| f.contains_key(name)) {
+            return Ok(Value::Num(self.fields.len() as f64));
+        }
+        match self.lookup(name) {
+            Some(Cell::Scalar(value)) => Ok(value.clone()),
+            Some(Cell::Array(_)) => Err(format!("can't use array {name} in scalar context")),
+            None => Ok(Value::Uninit),
+        }
+    }
+
+    fn set_var(&mut self, name: &str, value: Value) {
+        if let Some(frame) = self.frames.last_mut() {
+            if let Some(cell) = frame.get_mut(name) {
+                *cell = Cell::Scalar(value);
+                return;
+            }
+        }
+        if name == "NF" {
+            let count = value.num().max(0.0) as usize;
+            self.fields.resize(count, String::new());
+            self.rebuild();
+            return;
+        }
+        self.globals.insert(name.to_string(), Cell::Scalar(value));
+    }
+
+    /// The arena index of array NAME, creating it if the name is unused
+    fn array(&mut self, name: &str) -> Result<usize, String> {
+        let cell = match self.frames.last_mut().and_then(|frame| frame.get_mut(name)) {
+            Some(cell) => cell,
+            None => self
+                .globals
+                .entry(name.to_string())
+                .or_insert(Cell::Scalar(Value::Uninit)),
+        };
+        match cell {
+            Cell::Array(id) => Ok(*id),
+            Cell::Scalar(Value::Uninit) => {
+                self.arrays.push(Array::default());
+                let id = self.arrays.len() - 1;
+                *cell = Cell::Array(id);
+                Ok(id)
+            }
+            Cell::Scalar(_) => Err(format!("can't use scalar {name} as array")),
+        }
+    }
+
+    fn subscript(&mut self, subscripts: &[Expr]) -> Result<String, Stop> {
+        let mut parts = Vec::new();
+        for expr in subscripts {
+            let value = self.eval(expr)?;
+            parts.push(self.text(&value));
+        }
+        Ok(parts.join(&self.special("SUBSEP")))
+    }
+
+    fn field(&self, index: f64) -> Result<Value, String> {
+        if index < 0.0 {
+            return Err(format!("trying to access out of range field {index}"));
+        }
+        let index = index as usize;
+        Ok(match index {
+            0 => Value::Strnum(self.record.clone()),
+            _ => match self.fields.get(index - 1) {
+                Some(field) => Value::Strnum(field.clone()),
+                None => Value::Uninit,
+            },
+        })
+    }
+
+    fn set_field(&mut self, index: f64, text: String) -> Result<(), String> {
+        if index < 0.0 {
+            return Err(format!("trying to access out of range field {index}"));
+        }
+        let index = index as usize;
+        if index == 0 {
+            self.set_record(text);
+        } else {
+            if self.fields.len() < index {
+                self.fields.resize(index, String::new());
+            }
+            self.fields[index - 1] = text;
+            self.rebuild();
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), Stop> {
+        match target {
+            Expr::Var(name) => {
+                if matches!(self.lookup(name), Some(Cell::Array(_))) {
+                    return Err(Stop::Error(format!(
+                        "can't assign to {name}; it's an array name"
+                    )));
+                }
+                self.set_var(name, value);
+            }
+            Expr::Field(index) => {
+                let index = self.eval(index)?.num();
+                let text = self.text(&value);
+                self.set_field(index, text)?;
+            }
+            Expr::Index(name, subscripts) => {
+                let key = self.subscript(subscripts)?;
+                let id = self.array(name)?;
+                self.arrays[id].insert(key, value);
+            }
+            _ => return Err(Stop::Error("assignment to a non-variable".to_string())),
+        }
+        Ok(())
+    }
+
+    fn run_block(&mut self, statements: &[Stmt]) -> Result<Flow, Stop> {
+        for statement in statements {
+            match self.exec(statement)? {
+                Flow::Normal => {}
+                other => return Ok(other),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, statement: &Stmt) -> Result<Flow, Stop> {
+        match statement {
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::Print(args, redirect) => {
+                let mut parts = Vec::new();
+                if args.is_empty() {
+                    parts.push(self.record.clone());
+                }
+                for arg in args {
+                    let value = self.eval(arg)?;
+                    parts.push(match value {
+                        Value::Num(n) => number_text(n, &self.special("OFMT")),
+                        other => other.text(""),
+                    });
+                }
+                let text = parts.join(&self.special("OFS")) + &self.special("ORS");
+                self.output(redirect, &text)?;
+            }
+            Stmt::Printf(args, redirect) => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                let format = self.text(&values.remove(0));
+                let text = sprintf(&format, &values, &self.convfmt());
+                self.output(redirect, &text)?;
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.eval(condition)?.is_true() {
+                    return self.exec(then);
+                } else if let Some(otherwise) = otherwise {
+                    return self.exec(otherwise);
+                }
+            }
+            Stmt::While(condition, body) => {
+                while self.eval(condition)?.is_true() {
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => {}
+                    }
+                }
+            }
+            Stmt::Do(body, condition) => loop {
+                match self.exec(body)? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    _ => {}
+                }
+                if !self.eval(condition)?.is_true() {
+                    break;
+                }
+            },
+            Stmt::For(init, condition, step, body) => {
+                if let Some(init) = init {
+                    self.exec(init)?;
+                }
+                loop {
+                    if let Some(condition) = condition {
+                        if !self.eval(condition)?.is_true() {
+                            break;
+                        }
+                    }
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => {}
+                    }
+                    if let Some(step) = step {
+                        self.exec(step)?;
+                    }
+                }
+            }
+            Stmt::ForIn(key, array, body) => {
+                let id = self.array(array)?;
+                let keys = self.arrays[id].keys.clone();
+                for k in keys {
+                    if !self.arrays[id].values.contains_key(&k) {
+                        continue;
+                    }
+                    self.assign(&Expr::Var(key.clone()), Value::Strnum(k))?;
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => {}
+                    }
+                }
+            }
+            Stmt::Block(statements) => return self.run_block(statements),
+            Stmt::Next => return Err(Stop::Next),
+            Stmt::Exit(code) => {
+                if let Some(code) = code {
+                    self.exit_code = self.eval(code)?.num() as i32;
+                }
+                return Err(Stop::Exit);
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Uninit,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+            Stmt::Delete(name, subscripts) => {
+                let key = match subscripts {
+                    Some(subscripts) => Some(self.subscript(subscripts)?),
+                    None => None,
+                };
+                let id = self.array(name)?;
+                match key {
+                    Some(key) => self.arrays[id].remove(&key),
+                    None => self.arrays[id].clear(),
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Write print output to standard output or a redirection
+    fn output(&mut self, redirect: &Option<Redirect>, text: &str) -> Result<(), Stop> {
+        let Some(redirect) = redirect else {
+            self.stdout.extend(text.as_bytes());
+            return Ok(());
+        };
+        let value = self.eval(&redirect.target)?;
+        let name = self.text(&value);
+        match name.as_str() {
+            "/dev/stdout" | "-" => self.stdout.extend(text.as_bytes()),
+            "/dev/stderr" => self.stderr.push_str(text),
+            _ => {
+                if !self.outputs.contains_key(&name) {
+                    let path = self.cwd.join(&name);
+                    let file = if redirect.append {
+                        std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(path)
+                    } else {
+                        std::fs::File::create(path)
+                    };
+                    let file =
+                        file.map_err(|e| format!("can't redirect to {name}: {}", io_message(&e)))?;
+                    self.outputs.insert(name.clone(), file);
+                }
+                if let Some(file) = self.outputs.get_mut(&name) {
+                    file.write_all(text.as_bytes())
+                        .map_err(|e| format!("write to {name}: {}", io_message(&e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, Stop> {
+        Ok(match expr {
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Regex(pattern) => {
+                let regex = self.regex(pattern)?;
+                boolean(regex.is_match(&self.record))
+            }
+            Expr::Var(name) => self.get_var(name)?,
+            Expr::Field(index) => {
+                let index = self.eval(index)?.num();
+                self.field(index)?
+            }
+            Expr::Index(name, subscripts) => {
+                let key = self.subscript(subscripts)?;
+                let id = self.array(name)?;
+                let array = &mut self.arrays[id];
+                match array.values.get(&key) {
+                    Some(value) => value.clone(),
+                    None => {
+                        // Referencing an element creates it
+                        array.insert(key, Value::Uninit);
+                        Value::Uninit
+                    }
+                }
+            }
+            Expr::Assign(target, op, value) => {
+                let right = self.eval(value)?;
+                let result = match op {
+                    None => match right {
+                        Value::Uninit => Value::Uninit,
+                        other => other,
+                    },
+                    Some(op) => {
+                        let left = self.eval(target)?.num();
+                        Value::Num(arithmetic(*op, left, right.num())?)
+                    }
+                };
+                self.assign(target, result.clone())?;
+                result
+            }
+            Expr::Cond(condition, then, otherwise) => {
+                if self.eval(condition)?.is_true() {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::And(left, right) => {
+                boolean(self.eval(left)?.is_true() && self.eval(right)?.is_true())
+            }
+            Expr::Or(left, right) => {
+                boolean(self.eval(left)?.is_true() || self.eval(right)?.is_true())
+            }
+            Expr::Not(operand) => boolean(!self.eval(operand)?.is_true()),
+            Expr::Neg(operand) => Value::Num(-self.eval(operand)?.num()),
+            Expr::Plus(operand) => Value::Num(self.eval(operand)?.num()),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?.num();
+                let right = self.eval(right)?.num();
+                Value::Num(arithmetic(*op, left, right)?)
+            }
+            Expr::Compare(ordering, equal, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                let actual = if left.is_numeric() && right.is_numeric() {
+                    left.num()
+                        .partial_cmp(&right.num())
+                        .unwrap_or(Ordering::Less)
+                } else {
+                    self.text(&left).cmp(&self.text(&right))
+                };
+                boolean(match (ordering, equal) {
+                    (Ordering::Equal, true) => actual == Ordering::Equal,
+                    (Ordering::Equal, false) => actual != Ordering::Equal,
+                    (wanted, true) => actual == *wanted || actual == Ordering::Equal,
+                    (wanted, false) => actual == *wanted,
+                })
+            }
+            Expr::Match(negate, left, right) => {
+                let value = self.eval(left)?;
+                let text = self.text(&value);
+                let regex = self.regex_of(right)?;
+                boolean(regex.is_match(&text) != *negate)
+            }
+            Expr::Concat(left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                Value::Str(self.text(&left) + &self.text(&right))
+            }
+            Expr::In(keys, array) => {
+                let key = self.subscript(keys)?;
+                let id = self.array(array)?;
+                boolean(self.arrays[id].values.contains_key(&key))
+            }
+            Expr::Incr {
+                target,
+                delta,
+                prefix,
+            } => {
+                let old = self.eval(target)?.num();
+                self.assign(target, Value::Num(old + delta))?;
+                Value::Num(if *prefix { old + delta } else { old })
+            }
+            Expr::Call(name, args) => self.call(name, args)?,
+            Expr::Builtin(name, args) => self.builtin(name, args)?,
+            Expr::Getline { target, file } => self.getline(target.as_deref(), file.as_deref())?,
+            Expr::Group(_) => {
+                return Err(Stop::Error(
+                    "a parenthesized list is only allowed in print or before `in'".to_string(),
+                ))
+            }
+        })
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value, Stop> {
+        let program = self.program;
+        let function = program
+            .functions
+            .get(name)
+            .ok_or_else(|| format!("calling undefined function {name}"))?;
+        if args.len() > function.params.len() {
+            return Err(Stop::Error(format!(
+                "function {name} called with {} args, accepts only {}",
+                args.len(),
+                function.params.len()
+            )));
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(Stop::Error(format!(
+                "function {name}: call nesting too deep"
+            )));
+        }
+        let mut frame = HashMap::new();
+        for (i, param) in function.params.iter().enumerate() {
+            let cell = match args.get(i) {
+                // Arrays are passed by reference
+                Some(Expr::Var(var)) if matches!(self.lookup(var), Some(Cell::Array(_))) => self
+                    .lookup(var)
+                    .cloned()
+                    .unwrap_or(Cell::Scalar(Value::Uninit)),
+                Some(arg) => Cell::Scalar(self.eval(arg)?),
+                None => Cell::Scalar(Value::Uninit),
+            };
+            frame.insert(param.clone(), cell);
+        }
+        self.frames.push(frame);
+        let flow = self.run_block(&function.body);
+        let frame = self.frames.pop().unwrap_or_default();
+        // An untyped variable the function used as an array becomes one
+        for (arg, param) in args.iter().zip(&function.params) {
+            if let (Expr::Var(var), Some(Cell::Array(id))) = (arg, frame.get(param)) {
+                if matches!(self.lookup(var), None | Some(Cell::Scalar(Value::Uninit))) {
+                    match self.frames.last_mut().filter(|f| f.contains_key(var)) {
+                        Some(caller) => caller.insert(var.clone(), Cell::Array(*id)),
+                        None => self.globals.insert(var.clone(), Cell::Array(*id)),
+                    };
+                }
+            }
+        }
+        Ok(match flow? {
+            Flow::Return(value) => value,
+            _ => Value::Uninit,
+        })
+    }
+
+    fn getline(&mut self, target: Option<&Expr>, file: Option<&Expr>) -> Result<Value, Stop> {
+        let record = match file {
+            None => {
+                let record = self.next_record()?;
+                if record.is_some() {
+                    self.bump("NR");
+                    self.bump("FNR");
+                }
+                record
+            }
+            Some(file) => {
+                let value = self.eval(file)?;
+                let name = self.text(&value);
+                if !self.inputs.contains_key(&name) {
+                    let data = if name == "-" || name == "/dev/stdin" {
+                        let mut data = Vec::new();
+                        let _ = self.stdin.read_to_end(&mut data);
+                        data
+                    } else {
+                        match std::fs::read(self.cwd.join(&name)) {
+                            Ok(data) => data,
+                            Err(_) => return Ok(Value::Num(-1.0)),
+                        }
+                    };
+                    self.inputs.insert(name.clone(), Source::new(data));
+                }
+                let separator = self.special("RS");
+                let regex = if separator.chars().count() > 1 {
+                    Some(self.regex(&separator)?)
+                } else {
+                    None
+                };
+                let source = self.inputs.get_mut(&name);
+                source.and_then(|source| source.next(&separator, regex.as_ref()))
+            }
+        };
+        let Some(record) = record else {
+            return Ok(Value::Num(0.0));
+        };
+        match target {
+            Some(target) => self.assign(target, Value::Strnum(record))?,
+            None => self.set_record(record),
+        }
+        Ok(Value::Num(1.0))
+    }
+
+    fn builtin(&mut self, name: &str, args: &[Expr]) -> Result<Value, Stop> {
+        let arg = |interp: &mut Self, i: usize| -> Result<Value, Stop> {
+            match args.get(i) {
+                Some(expr) => interp.eval(expr),
+                None => Ok(Value::Uninit),
+            }
+        };
+        let count = |min: usize, max: usize| -> Result<(), Stop> {
+            if args.len() < min || args.len() > max {
+                Err(Stop::Error(format!("{name}: wrong number of arguments")))
+            } else {
+                Ok(())
+            }
+        };
+        Ok(match name {
+            "length" => {
+                count(0, 1)?;
+                match args.first() {
+                    None => Value::Num(self.record.chars().count() as f64),
+                    Some(Expr::Var(var)) if matches!(self.lookup(var), Some(Cell::Array(_))) => {
+                        let id = self.array(var)?;
+                        Value::Num(self.arrays[id].values.len() as f64)
+                    }
+                    Some(expr) => {
+                        let value = self.eval(expr)?;
+                        Value::Num(self.text(&value).chars().count() as f64)
+                    }
+                }
+            }
+            "substr" => {
+                count(2, 3)?;
+                let value = arg(self, 0)?;
+                let chars: Vec<char> = self.text(&value).chars().collect();
+                let start = round(arg(self, 1)?.num());
+                let end = if args.len() == 3 {
+                    start + round(arg(self, 2)?.num())
+                } else {
+                    f64::INFINITY
+                };
+                let from = start.max(1.0);
+                let to = end.min(chars.len() as f64 + 1.0);
+                Value::Str(if to <= from {
+                    String::new()
+                } else {
+                    chars[from as usize - 1..to as usize - 1].iter().collect()
+                })
+            }
+            "index" => {
+                count(2, 2)?;
+                let haystack = arg(self, 0)?;
+                let needle = arg(self, 1)?;
+                let (haystack, needle) = (self.text(&haystack), self.text(&needle));
+                Value::Num(match haystack.find(&needle) {
+                    Some(at) => haystack[..at].chars().count() as f64 + 1.0,
+                    None => 0.0,
+                })
+            }
+            "split" => {
+                count(2, 3)?;
+                let value = arg(self, 0)?;
+                let text = self.text(&value);
+                let Some(Expr::Var(array)) = args.get(1) else {
+                    return Err(Stop::Error(
+                        "split: second argument is not an array".to_string(),
+                    ));
+                };
+                let parts = self.split(&text, args.get(2));
+                let id = self.array(array)?;
+                self.arrays[id].clear();
+                for (i, part) in parts.iter().enumerate() {
+                    self.arrays[id].insert((i + 1).to_string(), Value::Strnum(part.clone()));
+                }
+                Value::Num(parts.len() as f64)
+            }
+            "sub" | "gsub" => {
+                count(2, 3)?;
+                let regex = self.regex_of(&args[0])?;
+                let replacement = arg(self, 1)?;
+                let replacement = self.text(&replacement);
+                let target = args
+                    .get(2)
+                    .cloned()
+                    .unwrap_or(Expr::Field(Box::new(Expr::Num(0.0))));
+                let value = self.eval(&target)?;
+                let text = self.text(&value);
+                let (result, changes) = substitute(&regex, &replacement, &text, name == "gsub");
+                if changes > 0 && is_lvalue(&target) {
+                    self.assign(&target, Value::Str(result))?;
+                }
+                Value::Num(changes as f64)
+            }
+            "match" => {
+                count(2, 2)?;
+                let value = arg(self, 0)?;
+                let text = self.text(&value);
+                let regex = self.regex_of(&args[1])?;
+                let (start, length) = match regex.find(&text) {
+                    Some(m) => (
+                        text[..m.start()].chars().count() as f64 + 1.0,
+                        m.as_str().chars().count() as f64,
+                    ),
+                    None => (0.0, -1.0),
+                };
+                self.set_var("RSTART", Value::Num(start));
+                self.set_var("RLENGTH", Value::Num(length));
+                Value::Num(start)
+            }
+            "sprintf" => {
+                if args.is_empty() {
+                    return Err(Stop::Error("sprintf: no format".to_string()));
+                }
+                let mut values = Vec::new();
+                for expr in args {
+                    values.push(self.eval(expr)?);
+                }
+                let format = self.text(&values.remove(0));
+                Value::Str(sprintf(&format, &values, &self.convfmt()))
+            }
+            "tolower" | "toupper" => {
+                count(1, 1)?;
+                let value = arg(self, 0)?;
+                let text = self.text(&value);
+                Value::Str(if name == "tolower" {
+                    text.to_lowercase()
+                } else {
+                    text.to_uppercase()
+                })
+            }
+            "int" => {
+                count(1, 1)?;
+                Value::Num(arg(self, 0)?.num().trunc())
+            }
+            "sqrt" | "exp" | "log" | "sin" | "cos" => {
+                count(1, 1)?;
+                let x = arg(self, 0)?.num();
+                Value::Num(match name {
+                    "sqrt" => x.sqrt(),
+                    "exp" => x.exp(),
+                    "log" => x.ln(),
+                    "sin" => x.sin(),
+                    _ => x.cos(),
+                })
+            }
+            "atan2" => {
+                count(2, 2)?;
+                let y = arg(self, 0)?.num();
+                let x = arg(self, 1)?.num();
+                Value::Num(y.atan2(x))
+            }
+            "rand" => {
+                count(0, 0)?;
+                // xorshift64*
+                self.random ^= self.random >> 12;
+                self.random ^= self.random << 25;
+                self.random ^= self.random >> 27;
+                let bits = self.random.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+                Value::Num(bits as f64 / (1u64 << 53) as f64)
+            }
+            "srand" => {
+                count(0, 1)?;
+                let previous = self.seed;
+                self.seed = match args.first() {
+                    Some(_) => arg(self, 0)?.num(),
+                    None => std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0.0, |d| d.as_secs() as f64),
+                };
+                self.random = (self.seed.to_bits() ^ 0x9e37_79b9_7f4a_7c15).max(1);
+                Value::Num(previous)
+            }
+            "close" => {
+                count(1, 1)?;
+                let value = arg(self, 0)?;
+                let name = self.text(&value);
+                let output = self.outputs.remove(&name).map(|mut file| file.flush());
+                let input = self.inputs.remove(&name);
+                Value::Num(if output.is_some() || input.is_some() {
+                    0.0
+                } else {
+                    -1.0
+                })
+            }
+            "fflush" => {
+                count(0, 1)?;
+                for file in self.outputs.values_mut() {
+                    let _ = file.flush();
+                }
+                Value::Num(0.0)
+            }
+            other => return Err(Stop::Error(format!("{other}: unknown function"))),
+        })
+    }
+}
+
+fn arithmetic(op: Arith, left: f64, right: f64) -> Result<f64, String> {
+    Ok(match op {
+        Arith::Add => left + right,
+        Arith::Sub => left - right,
+        Arith::Mul => left * right,
+        Arith::Div if right == 0.0 => return Err("division by zero".to_string()),
+        Arith::Div => left / right,
+        Arith::Mod if right == 0.0 => return Err("division by zero in %".to_string()),
+        Arith::Mod => left % right,
+        Arith::Pow => left.powf(right),
+    })
+}
+
+/// Round half away from zero, as `substr` positions are
+fn round(n: f64) -> f64 {
+    if n.is_nan() {
+        0.0
+    } else {
+        n.round()
+    }
+}
+
+/// Replace the first or every match of REGEX in TEXT, where `&` in the
+/// replacement is the match and `\&` a literal ampersand
+fn substitute(regex: &Regex, replacement: &str, text: &str, global: bool) -> (String, usize) {
+    let mut result = String::new();
+    let mut copied = 0;
+    let mut changes = 0;
+    let mut previous_end = None;
+    for found in regex.find_iter(text) {
+        // An empty match right after a match is skipped, as in other awks
+        if found.is_empty() && previous_end == Some(found.start()) {
+            continue;
+        }
+        previous_end = Some(found.end());
+        result.push_str(&text[copied..found.start()]);
+        let mut chars = replacement.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if matches!(chars.peek(), Some('&') | Some('\\')) => {
+                    result.push(chars.next().unwrap_or('\\'));
+                }
+                '&' => result.push_str(found.as_str()),
+                c => result.push(c),
+            }
+        }
+        copied = found.end();
+        changes += 1;
+        if !global {
+            break;
+        }
+    }
+    result.push_str(&text[copied..]);
+    (result, changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn awk(program: &str, input: &str) -> String {
+        awk_with(program, input, &[])
+    }
+
+    fn awk_with(program: &str, input: &str, operands: &[&str]) -> String {
+        let program = Parser::parse(program).unwrap();
+        let mut stdin = input.as_bytes();
+        let operands = operands.iter().map(|s| s.to_string()).collect();
+        let mut interp = Interp::new(&program, &mut stdin, PathBuf::from("."), operands);
+        interp.run().unwrap();
+        String::from_utf8(std::mem::take(&mut interp.stdout)).unwrap()
+    }
+
+    #[test]
+    fn formats_like_printf() {
+        let f = |format: &str, args: &[Value]| sprintf(format, args, "%.6g");
+        assert_eq!(
+            f(
+                "%5.2f|%-4d|%04d",
+                &[Value::Num(3.14159), Value::Num(7.0), Value::Num(-7.0)]
+            ),
+            " 3.14|7   |-007"
+        );
+        assert_eq!(
+            f(
+                "%x %o %X %#x",
+                &[
+                    Value::Num(255.0),
+                    Value::Num(8.0),
+                    Value::Num(255.0),
+                    Value::Num(255.0)
+                ]
+            ),
+            "ff 10 FF 0xff"
+        );
+        assert_eq!(f("%e", &[Value::Num(1234.5)]), "1.234500e+03");
+        assert_eq!(
+            f(
+                "%g %g %g",
+                &[Value::Num(0.0001), Value::Num(123456789.0), Value::Num(2.5)]
+            ),
+            "0.0001 1.23457e+08 2.5"
+        );
+        assert_eq!(
+            f(
+                "%s|%.2s|%5s|%c",
+                &[
+                    Value::Str("abc".into()),
+                    Value::Str("abc".into()),
+                    Value::Str("ab".into()),
+                    Value::Num(65.0)
+                ]
+            ),
+            "abc|ab|   ab|A"
+        );
+        assert_eq!(f("%*d%%", &[Value::Num(4.0), Value::Num(5.0)]), "   5%");
+        assert_eq!(number_text(0.1 + 0.2, "%.6g"), "0.3");
+        assert_eq!(number_text(1e6, "%.6g"), "1000000");
+    }
+
+    #[test]
+    fn splits_fields_and_counts_records() {
+        let input = "alice 30\nbob 25\n  carol   41  \n";
+        assert_eq!(
+            awk("{ print $2, $1 }", input),
+            "30 alice\n25 bob\n41 carol\n"
+        );
+        assert_eq!(awk("$2 > 28 { n++ } END { print n, NR }", input), "2 3\n");
+        assert_eq!(
+            awk("{ s += $2 } END { printf \"%.1f\\n\", s / NR }", input),
+            "32.0\n"
+        );
+        assert_eq!(
+            awk(
+                "BEGIN { FS = \":\"; OFS = \"-\" } { $1 = $1; print; print NF }",
+                "a:b:c\n"
+            ),
+            "a-b-c\n3\n"
+        );
+        assert_eq!(awk("{ NF = 2; print }", "a b c d\n"), "a b\n");
+        assert_eq!(awk("{ print $NF, $(NF-1) }", "a b c\n"), "c b\n");
+        assert_eq!(
+            awk(
+                "BEGIN { RS = \"\" } { print NR \": \" $1 }",
+                "a b\nc\n\n\nd\n"
+            ),
+            "1: a\n2: d\n"
+        );
+        assert_eq!(awk("/b/,/c/", "a\nb\nx\nc\nd\n"), "b\nx\nc\n");
+    }
+
+    #[test]
+    fn runs_control_flow_and_functions() {
+        let program = r#"
+            function fact(n) { return n <= 1 ? 1 : n * fact(n - 1) }
+            function fill(arr, n,   i) { for (i = 1; i <= n; i++) arr[i] = i * i }
+            BEGIN {
+                fill(sq, 3)
+                for (k in sq) out = out k "=" sq[k] " "
+                print out
+                print fact(5), length(sq), (2 in sq), (9 in sq)
+                i = 0
+                do { i += 2 } while (i < 7)
+                while (1) { if (++j > 3) break; else continue }
+                print i, j, 2 ^ 3 ^ 2, -2 ^ 2, 7 % 3
+                exit 3
+                print "unreachable"
+            }
+            END { print "end" }
+        "#;
+        assert_eq!(
+            awk(program, ""),
+            "1=1 2=4 3=9 \n120 3 1 0\n8 4 512 -4 1\nend\n"
+        );
+    }
+
+    #[test]
+    fn implements_string_functions() {
+        let program = r#"BEGIN {
+            s = "hello world"
+            print length(s), substr(s, 7), substr(s, 0, 3), substr(s, -1), index(s, "wor")
+            n = split("a:b::c", parts, ":")
+            print n, parts[1] parts[4], split("x y  z", w), w[3]
+            t = s; print gsub(/o/, "[&]", t), t
+            u = "aaa"; sub("a", "\\&", u); print u
+            print match("foobar", /o+b/), RSTART, RLENGTH
+            print toupper("abc") tolower("DEF"), sprintf("%03d", 7)
+            x = "10"; y = 9; print (x > y), ("10" > "9"), ($1 == 0)
+        }"#;
+        assert_eq!(
+            awk(program, ""),
+            "11 world he hello world 7\n4 ac 3 z\n2 hell[o] w[o]rld\n&aa\n2 2 3\nABCdef 007\n0 0 1\n"
+        );
+    }
+
+    #[test]
+    fn reads_with_getline_and_assignments() {
+        assert_eq!(
+            awk(
+                "NR == 1 { getline; print \"got\", $0 } END { print NR }",
+                "a\nb\nc\n"
+            ),
+            "got b\n3\n"
+        );
+        assert_eq!(
+            awk("{ getline line; print $0 \"+\" line }", "a\nb\n"),
+            "a+b\n"
+        );
+        assert_eq!(
+            awk_with("{ print x, $0 }", "in\n", &["x=1", "-", "x=2"]),
+            "1 in\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_programs() {
+        for program in [
+            "{ print ",
+            "BEGIN { x = }",
+            "{ print $1 | \"sort\" }",
+            "{ if (x) }",
+            "/abc",
+        ] {
+            assert!(Parser::parse(program).is_err(), "{program}");
+        }
+    }
+}
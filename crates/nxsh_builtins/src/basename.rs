@@ -0,0 +1,208 @@
+//! `basename` builtin - strip directories and a suffix from names
+//!
+//! Syntax:
+//!   basename NAME [SUFFIX]
+//!   basename -a [-s SUFFIX] [-z] NAME...
+//!
+//! Prints NAME without its leading directories or trailing separators, and
+//! without SUFFIX when NAME ends with it and is longer. `-a` takes every
+//! operand as a NAME, `-s` gives the SUFFIX and implies `-a`, and `-z` ends
+//! each line with NUL instead of a newline. Names are taken as text; the
+//! file system is not consulted.
+//!
+//! The exit status is 0 on success and 1 on a usage error.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
+use std::path::is_separator;
+
+/// The `basename` builtin command implementation
+pub struct BasenameCommand;
+
+impl Builtin for BasenameCommand {
+    fn name(&self) -> &'static str {
+        "basename"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Strip directories and a suffix from names"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print NAME with its leading directories removed, and SUFFIX too when given."
+    }
+
+    fn usage(&self) -> &'static str {
+        "basename NAME [SUFFIX] | basename -a [-s SUFFIX] [-z] NAME..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Strip directories and a suffix from names. Use 'basename src/main.rs .rs' to \
+         get 'main'."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run basename for the legacy dispatcher, writing straight to the
+/// process's standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// NAME without its directories; a name made only of separators keeps one
+fn strip_directories(name: &str) -> &str {
+    let trimmed = name.trim_end_matches(is_separator);
+    if trimmed.is_empty() {
+        return &name[..name.len().min(1)];
+    }
+    match trimmed.rfind(is_separator) {
+        Some(at) => &trimmed[at + 1..],
+        None => trimmed,
+    }
+}
+
+/// The last part of NAME, less SUFFIX unless that is all there is
+fn base_name<'n>(name: &'n str, suffix: &str) -> &'n str {
+    let base = strip_directories(name);
+    match base.strip_suffix(suffix) {
+        Some(stem) if !stem.is_empty() => stem,
+        _ => base,
+    }
+}
+
+fn run(args: &[String]) -> Outcome {
+    let usage = |message: String| Outcome {
+        stdout: Vec::new(),
+        stderr: format!("basename: {message}\n").into_bytes(),
+        status: 1,
+    };
+    let mut multiple = false;
+    let mut suffix = None;
+    let mut terminator = b'\n';
+    let mut names = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                names.extend(iter.by_ref().cloned());
+                break;
+            }
+            "--multiple" => multiple = true,
+            "--zero" => terminator = b'\0',
+            "--suffix" => match iter.next() {
+                Some(value) => suffix = Some(value.clone()),
+                None => return usage("option '--suffix' requires an argument".to_string()),
+            },
+            long if long.starts_with("--suffix=") => {
+                suffix = Some(long["--suffix=".len()..].to_string());
+            }
+            long if long.starts_with("--") => {
+                return usage(format!("unrecognized option '{long}'"));
+            }
+            short if short.len() > 1 && short.starts_with('-') => {
+                for (at, flag) in short[1..].char_indices() {
+                    match flag {
+                        'a' => multiple = true,
+                        'z' => terminator = b'\0',
+                        's' => {
+                            let attached = &short[2 + at..];
+                            suffix = match attached {
+                                "" => match iter.next() {
+                                    Some(value) => Some(value.clone()),
+                                    None => {
+                                        return usage(
+                                            "option requires an argument -- 's'".to_string(),
+                                        )
+                                    }
+                                },
+                                _ => Some(attached.to_string()),
+                            };
+                            break;
+                        }
+                        _ => return usage(format!("invalid option -- '{flag}'")),
+                    }
+                }
+            }
+            _ => names.push(arg.clone()),
+        }
+    }
+
+    if names.is_empty() {
+        return usage("missing operand".to_string());
+    }
+    // Without -a or -s a second operand is the suffix
+    if !multiple && suffix.is_none() {
+        match names.len() {
+            1 => {}
+            2 => suffix = names.pop(),
+            _ => return usage(format!("extra operand '{}'", names[2])),
+        }
+    }
+    let suffix = suffix.unwrap_or_default();
+    let mut stdout = Vec::new();
+    for name in &names {
+        stdout.extend_from_slice(base_name(name, &suffix).as_bytes());
+        stdout.push(terminator);
+    }
+    Outcome {
+        stdout,
+        stderr: Vec::new(),
+        status: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basename(args: &[&str]) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args);
+        let text = if outcome.status == 0 {
+            outcome.stdout
+        } else {
+            outcome.stderr
+        };
+        (String::from_utf8(text).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn strips_directories_and_suffix() {
+        assert_eq!(basename(&["/usr/lib/"]).0, "lib\n");
+        assert_eq!(basename(&["src/main.rs", ".rs"]).0, "main\n");
+        assert_eq!(basename(&[".rs", ".rs"]).0, ".rs\n");
+        assert_eq!(basename(&["//"]).0, "/\n");
+        assert_eq!(basename(&["plain"]).0, "plain\n");
+        assert_eq!(basename(&[""]).0, "\n");
+    }
+
+    #[test]
+    fn handles_many_names() {
+        assert_eq!(basename(&["-a", "a/b", "c/d"]).0, "b\nd\n");
+        assert_eq!(basename(&["-s", ".c", "x/one.c", "two.c"]).0, "one\ntwo\n");
+        assert_eq!(basename(&["-az", "a/b"]).0, "b\0");
+        assert_eq!(
+            basename(&["a", "b", "c"]),
+            ("basename: extra operand 'c'\n".into(), 1)
+        );
+        assert_eq!(basename(&[]).1, 1);
+    }
+}
@@ -30,6 +30,8 @@ use std::time::{Duration, Instant};
 // Beautiful CUI design
 use crate::ui_design::{ColorPalette, Colorize, Icons};
 
+use crate::common::decompress::{self, Decompress};
+
 // Advanced dependencies
 use content_inspector::{inspect, ContentType};
 use encoding_rs::{Encoding, ISO_8859_2, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
@@ -144,7 +146,7 @@ pub struct CatOptions {
     pub statistics: bool,
     pub buffer_size: usize,
     pub use_mmap: bool,
-    pub decompress: bool,
+    pub decompress: Decompress,
     pub follow_symlinks: bool,
     pub network_timeout: Duration,
 }
@@ -212,7 +214,7 @@ impl Default for CatOptions {
             statistics: false,
             buffer_size: BUFFER_SIZE,
             use_mmap: true,
-            decompress: true,
+            decompress: Decompress::Files,
             follow_symlinks: true,
             network_timeout: Duration::from_secs(30),
         }
@@ -384,8 +386,11 @@ fn parse_cat_args(args: &[String]) -> Result<CatOptions> {
             "--no-mmap" => {
                 options.use_mmap = false;
             }
+            "-z" | "--decompress" => {
+                options.decompress = Decompress::Always;
+            }
             "--no-decompress" => {
-                options.decompress = false;
+                options.decompress = Decompress::Never;
             }
             "--no-follow-symlinks" => {
                 options.follow_symlinks = false;
@@ -441,6 +446,7 @@ fn parse_cat_args(args: &[String]) -> Result<CatOptions> {
                         'T' => options.show_tabs = true,
                         'u' => {} // Ignored
                         'v' => options.show_nonprinting = true,
+                        'z' => options.decompress = Decompress::Always,
                         _ => return Err(anyhow!(t!("error-invalid-option", "option" => arg))),
                     }
                 }
@@ -457,8 +463,7 @@ fn parse_cat_args(args: &[String]) -> Result<CatOptions> {
 }
 
 fn process_stdin(options: &CatOptions) -> Result<()> {
-    let stdin = io::stdin();
-    let reader = stdin.lock();
+    let reader = stdin_reader(options)?;
     let stdout = io::stdout();
     let mut writer = BufWriter::new(stdout.lock());
 
@@ -471,6 +476,15 @@ fn process_stdin(options: &CatOptions) -> Result<()> {
     Ok(())
 }
 
+/// Standard input, expanded when `-z` asks for it
+fn stdin_reader(options: &CatOptions) -> Result<Box<dyn BufRead>> {
+    let stdin = io::stdin().lock();
+    if options.decompress.applies(true) {
+        return decompress::decompressed(stdin).context(t!("error-io-error"));
+    }
+    Ok(Box::new(stdin))
+}
+
 fn process_files_sequential(options: &CatOptions) -> Result<()> {
     let mut total_stats = FileStats {
         bytes_read: 0,
@@ -617,8 +631,7 @@ fn process_single_file(
     }
 
     if _filename == "-" {
-        let stdin = io::stdin();
-        let reader = stdin.lock();
+        let reader = stdin_reader(options)?;
         let stdout = io::stdout();
         let mut writer = BufWriter::new(stdout.lock());
 
@@ -657,7 +670,7 @@ fn process_single_file(
 
     // Detect file type and compression
     let file_type = detect_file_type(&final_path)?;
-    let compression = if options.decompress {
+    let compression = if options.decompress.applies(false) {
         detect_compression(&final_path)?
     } else {
         None
@@ -816,35 +829,9 @@ fn process_file_stream<W: Write + ?Sized>(
         None
     };
 
+    // The suffix may be misleading, so the data's magic number decides
     let reader: Box<dyn BufRead> = match compression {
-        Some(CompressionType::Gzip) => {
-            // Pure Rust decompression disabled for now
-            // Box::new(BufReader::new(GzDecoder::new(file)))
-            Box::new(BufReader::new(file))
-        }
-        Some(CompressionType::Bzip2) => {
-            // Use pure Rust alternative for bzip2
-            // For now, treat as regular file
-            eprintln!("{}", t!("cat-warn-bzip2-missing"));
-            Box::new(BufReader::with_capacity(options.buffer_size, file))
-        }
-        Some(CompressionType::Xz) => {
-            // Use pure Rust alternative for XZ
-            // For now, treat as regular file
-            eprintln!("{}", t!("cat-warn-xz-missing"));
-            Box::new(BufReader::with_capacity(options.buffer_size, file))
-        }
-        Some(CompressionType::Zstd) => {
-            // Use pure Rust alternative for zstd
-            // For now, treat as regular file
-            eprintln!("{}", t!("cat-warn-zstd-missing"));
-            Box::new(BufReader::with_capacity(options.buffer_size, file))
-        }
-        Some(CompressionType::Deflate) => {
-            // Pure Rust decompression disabled for now
-            // Box::new(BufReader::new(DeflateDecoder::new(file)))
-            Box::new(BufReader::new(file))
-        }
+        Some(_) => decompress::decompressed(file).context(t!("error-io-error"))?,
         None => Box::new(BufReader::with_capacity(options.buffer_size, file)),
     };
 
@@ -1123,7 +1110,7 @@ fn process_url_to_writer(
     if scheme == "file" {
         if let Ok(path_buf) = url.to_file_path() {
             // Reuse local file streaming path. Detect compression if enabled.
-            let compression = if options.decompress {
+            let compression = if options.decompress.applies(false) {
                 detect_compression(&path_buf)?
             } else {
                 None
@@ -4,6 +4,7 @@
 //! with support for various shell features like directory stack,
 //! CDPATH, and symbolic link handling.
 
+use crate::common::io_message;
 use nxsh_core::context::ShellContext;
 use nxsh_core::error::{IoErrorKind, RuntimeErrorKind};
 use nxsh_core::{Builtin, ErrorKind, ExecutionResult, ShellError, ShellResult};
@@ -25,11 +26,12 @@ impl Builtin for CdCommand {
     }
 
     fn description(&self) -> &'static str {
-        "Change the current working directory to DIR. The default DIR is the value of the HOME environment variable."
+        "Change the current working directory to DIR. The default DIR is the value of the HOME environment variable, \
+         '-' is the previous directory and relative names are also looked for in the directories listed in CDPATH."
     }
 
     fn usage(&self) -> &'static str {
-        "cd [-L|-P] [DIR]"
+        "cd [-L|-P] [DIR | -]"
     }
 
     fn help(&self) -> &'static str {
@@ -84,7 +86,7 @@ impl Builtin for CdCommand {
 
         // Determine target directory
         let target = match target_dir {
-            Some(dir) => self.resolve_target_directory(&dir, ctx)?,
+            Some(dir) => dir,
             None => {
                 // No argument - go to HOME directory
                 ctx.get_var("HOME").ok_or_else(|| {
@@ -96,47 +98,65 @@ impl Builtin for CdCommand {
             }
         };
 
-        // Handle special cases
-        let resolved_target = match target.as_str() {
-            "-" => {
-                // Go to previous directory (stored in OLDPWD)
-                let oldpwd = ctx.get_var("OLDPWD").ok_or_else(|| {
-                    ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::VariableNotFound),
-                        "cd: OLDPWD not set",
-                    )
-                })?;
+        let (new_dir, announce) = self.change_directory(ctx, &target, !follow_symlinks)?;
 
-                // Print the directory we're changing to (bash behavior)
-                println!("{oldpwd}");
-                oldpwd
-            }
-            _ => target,
-        };
+        // Like bash, show where `cd -` and a CDPATH match went
+        let mut result = ExecutionResult::success(0);
+        if announce {
+            result = result.with_output(format!("{}\n", new_dir.display()).into_bytes());
+        }
+        Ok(result)
+    }
+}
 
-        // Save current directory as OLDPWD
-        let current_dir = env::current_dir().map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(IoErrorKind::PermissionError),
-                format!("Failed to get current directory: {e}"),
-            )
-        })?;
+impl CdCommand {
+    /// Create a new cd command instance
+    pub fn new() -> Self {
+        Self
+    }
 
-        // Change to the target directory
-        let new_path = PathBuf::from(&resolved_target);
-        let canonical_path = if follow_symlinks {
-            self.canonicalize_path(&new_path)?
+    /// Change the shell to `target` as `cd` does: `-` is `$OLDPWD` and a
+    /// relative name may be found through CDPATH. Updates OLDPWD and PWD and
+    /// returns the new directory and whether it should be shown, which it
+    /// should for `-` and CDPATH matches. `pushd` and `popd` move through here.
+    pub(crate) fn change_directory(
+        &self,
+        ctx: &mut ShellContext,
+        target: &str,
+        physical: bool,
+    ) -> ShellResult<(PathBuf, bool)> {
+        let (resolved_target, announce) = if target == "-" {
+            // Go to previous directory (stored in OLDPWD)
+            let oldpwd = ctx.get_var("OLDPWD").ok_or_else(|| {
+                ShellError::new(
+                    ErrorKind::RuntimeError(RuntimeErrorKind::VariableNotFound),
+                    "cd: OLDPWD not set",
+                )
+            })?;
+            (oldpwd, true)
         } else {
-            self.resolve_path_without_symlinks(&new_path)?
+            self.resolve_target_directory(target, ctx)
         };
 
-        // Actually change directory
-        env::set_current_dir(&canonical_path).map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(IoErrorKind::NotFound),
-                format!("cd: {resolved_target}: {e}"),
-            )
-        })?;
+        // Relative names are taken from the shell's directory, not the process's
+        let current_dir = ctx.cwd.clone();
+        let new_path = current_dir.join(&resolved_target);
+        let canonical_path = if physical {
+            self.resolve_path_without_symlinks(&new_path, &resolved_target)?
+        } else {
+            self.canonicalize_path(&new_path, &resolved_target)?
+        };
+
+        // A subshell shares this process, so its directory lives only in its
+        // own context; moving the process would leak into the parent
+        if !ctx.is_subshell() {
+            env::set_current_dir(&canonical_path).map_err(|e| {
+                ShellError::new(
+                    ErrorKind::IoError(IoErrorKind::NotFound),
+                    format!("cd: {resolved_target}: {}", io_message(&e)),
+                )
+            })?;
+        }
 
         // Update shell context
         ctx.cwd = canonical_path.clone();
@@ -145,67 +165,61 @@ impl Builtin for CdCommand {
         ctx.set_var("OLDPWD", current_dir.to_string_lossy().to_string());
         ctx.set_var("PWD", canonical_path.to_string_lossy().to_string());
 
-        // Add to directory stack if pushd/popd is being used
-        // (This would integrate with a directory stack implementation)
-
         // Check for directory-specific actions
         self.check_directory_hooks(&canonical_path, ctx)?;
 
-        Ok(ExecutionResult::success(0))
-    }
-}
-
-impl CdCommand {
-    /// Create a new cd command instance
-    pub fn new() -> Self {
-        Self
+        Ok((canonical_path, announce))
     }
 
-    /// Resolve target directory using CDPATH if necessary
-    fn resolve_target_directory(&self, target: &str, ctx: &ShellContext) -> ShellResult<String> {
+    /// Resolve target directory using CDPATH if necessary, returning the
+    /// directory and whether it came from a non-empty CDPATH entry
+    ///
+    /// As in POSIX, CDPATH is searched first, an empty entry standing for
+    /// the working directory, and the working directory is tried last.
+    fn resolve_target_directory(&self, target: &str, ctx: &ShellContext) -> (String, bool) {
         let path = Path::new(target);
 
-        // If path is absolute or starts with ./ or ../, use it directly
-        if path.is_absolute() || target.starts_with("./") || target.starts_with("../") {
-            return Ok(target.to_string());
+        // If path is absolute or starts with . or .., use it directly
+        let explicit = path.is_absolute()
+            || matches!(
+                path.components().next(),
+                Some(std::path::Component::CurDir | std::path::Component::ParentDir)
+            );
+        if explicit {
+            return (target.to_string(), false);
         }
 
-        // Try current directory first
-        let current_attempt = env::current_dir()
-            .map_err(|e| {
-                ShellError::new(
-                    ErrorKind::IoError(IoErrorKind::PermissionError),
-                    format!("Failed to get current directory: {e}"),
-                )
-            })?
-            .join(target);
-
-        if current_attempt.exists() {
-            return Ok(target.to_string());
-        }
-
-        // Try CDPATH
-        if let Some(cdpath) = ctx.get_var("CDPATH") {
+        if let Some(cdpath) = ctx.get_var("CDPATH").filter(|p| !p.is_empty()) {
             for path_dir in env::split_paths(&cdpath) {
-                let candidate = path_dir.join(target);
-                if candidate.exists() && candidate.is_dir() {
-                    return Ok(candidate.to_string_lossy().to_string());
+                let here = path_dir.as_os_str().is_empty();
+                let candidate = ctx.cwd.join(&path_dir).join(target);
+                if candidate.is_dir() {
+                    if here {
+                        return (target.to_string(), false);
+                    }
+                    return (candidate.to_string_lossy().to_string(), true);
                 }
             }
         }
 
-        // If not found anywhere, return original target (will likely fail later)
-        Ok(target.to_string())
+        // Not found through CDPATH: relative to the working directory
+        (target.to_string(), false)
     }
 
-    /// Canonicalize path (resolve symlinks)
-    fn canonicalize_path(&self, path: &Path) -> ShellResult<PathBuf> {
+    /// Canonicalize path (resolve symlinks), naming it as `shown` in errors
+    fn canonicalize_path(&self, path: &Path, shown: &str) -> ShellResult<PathBuf> {
         let p = path.canonicalize().map_err(|e| {
             ShellError::new(
                 ErrorKind::IoError(IoErrorKind::NotFound),
-                format!("cd: {}: {}", path.display(), e),
+                format!("cd: {shown}: {}", io_message(&e)),
             )
         })?;
+        if !p.is_dir() {
+            return Err(ShellError::new(
+                ErrorKind::IoError(IoErrorKind::InvalidData),
+                format!("cd: {shown}: Not a directory"),
+            ));
+        }
 
         #[cfg(windows)]
         {
@@ -217,18 +231,9 @@ impl CdCommand {
         }
     }
 
-    /// Resolve path without following symlinks
-    fn resolve_path_without_symlinks(&self, path: &Path) -> ShellResult<PathBuf> {
-        let mut result = if path.is_absolute() {
-            PathBuf::from("/")
-        } else {
-            env::current_dir().map_err(|e| {
-                ShellError::new(
-                    ErrorKind::IoError(IoErrorKind::PermissionError),
-                    format!("Failed to get current directory: {e}"),
-                )
-            })?
-        };
+    /// Resolve an absolute path without following symlinks
+    fn resolve_path_without_symlinks(&self, path: &Path, shown: &str) -> ShellResult<PathBuf> {
+        let mut result = PathBuf::from("/");
 
         for component in path.components() {
             match component {
@@ -242,7 +247,7 @@ impl CdCommand {
                     result.push(name);
                 }
                 std::path::Component::RootDir => {
-                    result = PathBuf::from("/");
+                    result.push(std::path::MAIN_SEPARATOR_STR);
                 }
                 std::path::Component::Prefix(prefix) => {
                     // Windows drive letters, etc.
@@ -255,14 +260,14 @@ impl CdCommand {
         if !result.exists() {
             return Err(ShellError::new(
                 ErrorKind::IoError(IoErrorKind::NotFound),
-                format!("cd: {}: No such file or directory", path.display()),
+                format!("cd: {shown}: No such file or directory"),
             ));
         }
 
         if !result.is_dir() {
             return Err(ShellError::new(
                 ErrorKind::IoError(IoErrorKind::InvalidData),
-                format!("cd: {}: Not a directory", path.display()),
+                format!("cd: {shown}: Not a directory"),
             ));
         }
 
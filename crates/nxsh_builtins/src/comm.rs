@@ -1,80 +1,263 @@
-//! `comm` command  Ecompare two sorted files line by line.
+//! `comm` builtin - compare two sorted files line by line
 //!
-//! Minimal subset:
-//!   comm FILE1 FILE2
-//!   • Assumes both files are sorted lexicographically.
-//!   • Output has three TAB-separated columns:
-//!       col1: lines only in FILE1
-//!       col2: lines only in FILE2
-//!       col3: lines common to both
-//!   • No column suppression options (-1/-2/-3) implemented yet.
+//! Syntax:
+//!   comm [-123] [--output-delimiter=STR] [--total] [--check-order | --nocheck-order]
+//!        FILE1 FILE2
 //!
-//! FILE of "-" refers to STDIN (only for FILE1 because STDIN can be read once).
+//! Output comes in three columns: lines only in FILE1, lines only in FILE2
+//! and lines in both. Each column is indented by one TAB, or STR, per
+//! column before it that is printed; `-1`, `-2` and `-3` suppress the
+//! corresponding column. `--total` ends with a line counting each column.
+//! Either FILE may be `-` for standard input.
+//!
+//! The exit status is 0 on success and 1 when a FILE cannot be read or is
+//! not sorted.
 
-use anyhow::{anyhow, Result};
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 use std::cmp::Ordering;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
 
-pub fn comm_cli(args: &[String]) -> Result<()> {
-    if args.len() < 2 {
-        return Err(anyhow!("comm: missing file operands"));
+/// The `comm` builtin command implementation
+pub struct CommCommand;
+
+impl Builtin for CommCommand {
+    fn name(&self) -> &'static str {
+        "comm"
     }
-    let f1 = &args[0];
-    let f2 = &args[1];
 
-    let reader1: Box<dyn BufRead> = if f1 == "-" {
-        Box::new(BufReader::new(io::stdin()))
-    } else {
-        Box::new(BufReader::new(File::open(Path::new(f1))?))
-    };
-    let reader2: Box<dyn BufRead> = Box::new(BufReader::new(File::open(Path::new(f2))?));
+    fn synopsis(&self) -> &'static str {
+        "Compare two sorted files line by line"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write the lines only in FILE1, only in FILE2 and in both as three columns; \
+         -1, -2 and -3 suppress a column."
+    }
 
-    comm_streams(reader1, reader2)?;
-    Ok(())
+    fn usage(&self) -> &'static str {
+        "comm [-123] [--output-delimiter=STR] [--total] FILE1 FILE2"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compare two sorted files line by line. Use 'comm -12 a b' to print the common lines."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
 }
 
-fn comm_streams<R1: BufRead, R2: BufRead>(mut r1: R1, mut r2: R2) -> Result<()> {
-    let mut l1 = String::new();
-    let mut l2 = String::new();
-    let mut eof1 = r1.read_line(&mut l1)? == 0;
-    let mut eof2 = r2.read_line(&mut l2)? == 0;
-    let mut out = io::stdout();
-
-    while !(eof1 && eof2) {
-        if eof2 {
-            writeln!(out, "{}", l1.trim_end())?;
-            l1.clear();
-            eof1 = r1.read_line(&mut l1)? == 0;
-            continue;
+/// Run comm for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    /// Whether each of the three columns is printed
+    columns: [bool; 3],
+    delimiter: String,
+    total: bool,
+    check_order: bool,
+    files: [String; 2],
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            columns: [true; 3],
+            delimiter: "\t".to_string(),
+            total: false,
+            check_order: true,
+            files: Default::default(),
+        };
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--total" => options.total = true,
+                "--check-order" => options.check_order = true,
+                "--nocheck-order" => options.check_order = false,
+                "--output-delimiter" => {
+                    options.delimiter = iter.next().cloned().ok_or_else(|| {
+                        "option '--output-delimiter' requires an argument".to_string()
+                    })?;
+                }
+                long if long.starts_with("--output-delimiter=") => {
+                    options.delimiter = long["--output-delimiter=".len()..].to_string();
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for flag in short[1..].chars() {
+                        match flag {
+                            '1' => options.columns[0] = false,
+                            '2' => options.columns[1] = false,
+                            '3' => options.columns[2] = false,
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
         }
-        if eof1 {
-            writeln!(out, "\t{}", l2.trim_end())?;
-            l2.clear();
-            eof2 = r2.read_line(&mut l2)? == 0;
-            continue;
+        match <[String; 2]>::try_from(operands) {
+            Ok(files) => options.files = files,
+            Err(operands) if operands.len() < 2 => {
+                return Err(match operands.first() {
+                    Some(file) => format!("missing operand after '{file}'"),
+                    None => "missing operand".to_string(),
+                })
+            }
+            Err(operands) => return Err(format!("extra operand '{}'", operands[2])),
         }
-        match l1.trim_end().cmp(l2.trim_end()) {
-            Ordering::Equal => {
-                writeln!(out, "\t\t{}", l1.trim_end())?;
-                l1.clear();
-                l2.clear();
-                eof1 = r1.read_line(&mut l1)? == 0;
-                eof2 = r2.read_line(&mut l2)? == 0;
+        Ok(options)
+    }
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("comm: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let mut inputs = Inputs::new(cwd, stdin);
+    let mut contents = Vec::new();
+    for name in &options.files {
+        match inputs.read(name) {
+            Ok(data) => contents.push(data),
+            Err(e) => {
+                outcome.stderr = format!("comm: {name}: {}\n", io_message(&e)).into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+        }
+    }
+    let (one, two) = (lines(&contents[0]), lines(&contents[1]));
+    if options.check_order {
+        for (number, file) in [&one, &two].into_iter().enumerate() {
+            if file.windows(2).any(|pair| pair[0] > pair[1]) {
+                outcome.stderr.extend(
+                    format!("comm: file {} is not in sorted order\n", number + 1).as_bytes(),
+                );
+                outcome.status = 1;
             }
+        }
+    }
+
+    let mut counts = [0usize; 3];
+    let mut write = |column: usize, line: &[u8]| {
+        counts[column] += 1;
+        if !options.columns[column] {
+            return;
+        }
+        for shown in &options.columns[..column] {
+            if *shown {
+                outcome
+                    .stdout
+                    .extend_from_slice(options.delimiter.as_bytes());
+            }
+        }
+        outcome.stdout.extend_from_slice(line);
+        outcome.stdout.push(b'\n');
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < one.len() || j < two.len() {
+        let order = match (one.get(i), two.get(j)) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        match order {
             Ordering::Less => {
-                writeln!(out, "{}", l1.trim_end())?;
-                l1.clear();
-                eof1 = r1.read_line(&mut l1)? == 0;
+                write(0, one[i]);
+                i += 1;
             }
             Ordering::Greater => {
-                writeln!(out, "\t{}", l2.trim_end())?;
-                l2.clear();
-                eof2 = r2.read_line(&mut l2)? == 0;
+                write(1, two[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                write(2, one[i]);
+                i += 1;
+                j += 1;
             }
         }
     }
-    Ok(())
-} 
+    if options.total {
+        let [only_one, only_two, both] = counts;
+        let delimiter = &options.delimiter;
+        outcome.stdout.extend(
+            format!("{only_one}{delimiter}{only_two}{delimiter}{both}{delimiter}total\n")
+                .as_bytes(),
+        );
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn comm(args: &[&str], dir: &Path) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut io::empty());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn splits_lines_into_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "apple\nfig\npear\n").unwrap();
+        std::fs::write(dir.path().join("b"), "fig\nplum\n").unwrap();
+        assert_eq!(
+            comm(&["a", "b"], dir.path()),
+            ("apple\n\t\tfig\npear\n\tplum\n".into(), 0)
+        );
+        assert_eq!(comm(&["-12", "a", "b"], dir.path()).0, "fig\n");
+        assert_eq!(
+            comm(
+                &["-3", "--output-delimiter=|", "--total", "a", "b"],
+                dir.path()
+            )
+            .0,
+            "apple\npear\n|plum\n2|1|1|total\n"
+        );
+
+        std::fs::write(dir.path().join("c"), "b\na\n").unwrap();
+        assert_eq!(comm(&["c", "b"], dir.path()).1, 1);
+    }
+}
@@ -0,0 +1,196 @@
+//! Transparent decompression for text builtins
+//!
+//! `decompressed` wraps a reader so that gzip, bzip2, xz or zstd data,
+//! recognised by its magic number, reads as its plain contents while other
+//! data passes through untouched. That lets `grep foo log.gz` and
+//! `head -z < log.xz` work without a separate decompressor in the pipeline.
+//!
+//! Each codec needs the feature of its compression command. Data in a codec
+//! that is not built in is an error instead of being passed through, so that
+//! nobody searches compressed bytes unawares.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// A compression format input can be in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Codec {
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Bzip2 => "bzip2",
+            Codec::Xz => "xz",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    /// The codec whose magic number DATA starts with
+    pub fn detect(data: &[u8]) -> Option<Codec> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if data.starts_with(b"BZh") {
+            Some(Codec::Bzip2)
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+            Some(Codec::Xz)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// The codec a file called NAME is compressed with, going by its suffix
+    pub fn from_suffix(name: &str) -> Option<Codec> {
+        let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" | "tgz" | "taz" => Some(Codec::Gzip),
+            "bz2" | "tbz" | "tbz2" | "tb2" => Some(Codec::Bzip2),
+            "xz" | "txz" => Some(Codec::Xz),
+            "zst" | "tzst" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Which inputs a text builtin decompresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decompress {
+    /// Named files only, the default: a pipe is more likely to be feeding
+    /// compressed bytes on purpose
+    Files,
+    /// Standard input too (`-z`)
+    Always,
+    /// Nothing (`--no-decompress`)
+    Never,
+}
+
+impl Decompress {
+    /// Whether input read from standard input (STDIN) or a file is decoded
+    pub fn applies(self, stdin: bool) -> bool {
+        match self {
+            Decompress::Files => !stdin,
+            Decompress::Always => true,
+            Decompress::Never => false,
+        }
+    }
+}
+
+/// The first bytes of INPUT, and INPUT from its start again
+fn peek<'a>(mut input: impl Read + 'a) -> io::Result<(Vec<u8>, impl Read + 'a)> {
+    let mut head = vec![0; 6];
+    let mut len = 0;
+    while len < head.len() {
+        match input.read(&mut head[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    head.truncate(len);
+    Ok((head.clone(), io::Cursor::new(head).chain(input)))
+}
+
+/// INPUT with compressed data decoded; other data reads unchanged
+pub fn decompressed<'a>(input: impl Read + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    let (head, input) = peek(input)?;
+    let input = BufReader::new(input);
+    match Codec::detect(&head) {
+        Some(codec) => Ok(Box::new(BufReader::new(decoder(codec, input)?))),
+        None => Ok(Box::new(input)),
+    }
+}
+
+/// Everything INPUT holds, decoded first when DECODE is set
+pub fn read_to_end(input: impl Read, decode: bool) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match decode {
+        true => decompressed(input)?.read_to_end(&mut data)?,
+        false => BufReader::new(input).read_to_end(&mut data)?,
+    };
+    Ok(data)
+}
+
+/// The codec the file at PATH is compressed with, if any
+pub fn sniff(path: &Path) -> io::Result<Option<Codec>> {
+    let (head, _) = peek(std::fs::File::open(path)?)?;
+    Ok(Codec::detect(&head))
+}
+
+/// INPUT decoded from CODEC
+pub fn decoder<'a>(codec: Codec, input: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    match codec {
+        #[cfg(feature = "compression-gzip")]
+        Codec::Gzip => Ok(Box::new(flate2::bufread::MultiGzDecoder::new(input))),
+        #[cfg(feature = "compression-bzip2")]
+        Codec::Bzip2 => Ok(Box::new(bzip2_rs::DecoderReader::new(input))),
+        #[cfg(feature = "compression-lzma")]
+        Codec::Xz => {
+            let mut input = input;
+            let mut data = Vec::new();
+            lzma_rs::xz_decompress(&mut input, &mut data)
+                .map_err(|e| invalid(format!("xz: {e}")))?;
+            Ok(Box::new(io::Cursor::new(data)))
+        }
+        #[cfg(feature = "compression-zstd")]
+        Codec::Zstd => {
+            let decoder = ruzstd::streaming_decoder::StreamingDecoder::new(input)
+                .map_err(|e| invalid(format!("zstd: {e}")))?;
+            Ok(Box::new(decoder))
+        }
+        #[allow(unreachable_patterns)]
+        codec => {
+            let _ = (input, invalid);
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} support is not built in", codec.name()),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_codecs_by_magic_and_suffix() {
+        assert_eq!(Codec::detect(&[0x1f, 0x8b, 8]), Some(Codec::Gzip));
+        assert_eq!(Codec::detect(b"\xfd7zXZ\0rest"), Some(Codec::Xz));
+        assert_eq!(Codec::detect(b"plain"), None);
+        assert_eq!(Codec::from_suffix("a.tar.zst"), Some(Codec::Zstd));
+        assert_eq!(Codec::from_suffix("notes.txt"), None);
+        assert!(Decompress::Files.applies(false) && !Decompress::Files.applies(true));
+    }
+
+    #[test]
+    fn passes_plain_input_through() {
+        let mut out = String::new();
+        decompressed(&b"ab"[..])
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "ab");
+    }
+
+    #[cfg(feature = "compression-lzma")]
+    #[test]
+    fn decodes_compressed_input() {
+        let mut packed = Vec::new();
+        lzma_rs::xz_compress(&mut &b"hello\n"[..], &mut packed).unwrap();
+        let mut out = String::new();
+        decompressed(packed.as_slice())
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "hello\n");
+    }
+}
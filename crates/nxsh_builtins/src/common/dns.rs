@@ -0,0 +1,734 @@
+//! A small DNS client
+//!
+//! Queries are sent over UDP, and again over TCP when the answer comes back
+//! truncated, following RFC 1035 with the AAAA (RFC 3596) and SRV
+//! (RFC 2782) record types; other types are kept as raw data and shown in
+//! the RFC 3597 form. The system's name servers are read from
+//! `/etc/resolv.conf` on Unix and from the network parameters on Windows.
+
+use rand::{rngs::OsRng, RngCore};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+const CLASS_IN: u16 = 1;
+
+/// A record type by its number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordType(pub u16);
+
+/// The types known by name
+const TYPES: &[(&str, u16)] = &[
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+    ("ANY", 255),
+];
+
+impl RecordType {
+    pub const A: RecordType = RecordType(1);
+    pub const NS: RecordType = RecordType(2);
+    pub const CNAME: RecordType = RecordType(5);
+    pub const SOA: RecordType = RecordType(6);
+    pub const PTR: RecordType = RecordType(12);
+    pub const MX: RecordType = RecordType(15);
+    pub const TXT: RecordType = RecordType(16);
+    pub const AAAA: RecordType = RecordType(28);
+    pub const SRV: RecordType = RecordType(33);
+
+    /// A type by name in any case, or as `TYPE` and its number
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.to_ascii_uppercase();
+        if let Some(&(_, code)) = TYPES.iter().find(|(known, _)| *known == upper) {
+            return Some(RecordType(code));
+        }
+        upper.strip_prefix("TYPE")?.parse().ok().map(RecordType)
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match TYPES.iter().find(|(_, code)| *code == self.0) {
+            Some((name, _)) => f.write_str(name),
+            None => write!(f, "TYPE{}", self.0),
+        }
+    }
+}
+
+/// The data of a record
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// The target of an NS, CNAME or PTR record
+    Name(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Txt(Vec<Vec<u8>>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Soa {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    Other(Vec<u8>),
+}
+
+/// The data as zone files and `dig` show it
+impl fmt::Display for RecordData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordData::A(address) => write!(f, "{address}"),
+            RecordData::Aaaa(address) => write!(f, "{address}"),
+            RecordData::Name(name) => f.write_str(name),
+            RecordData::Mx {
+                preference,
+                exchange,
+            } => write!(f, "{preference} {exchange}"),
+            RecordData::Txt(strings) => {
+                for (index, text) in strings.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" ")?;
+                    }
+                    f.write_str("\"")?;
+                    for &byte in text {
+                        match byte {
+                            b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+                            b' '..=b'~' => write!(f, "{}", byte as char)?,
+                            _ => write!(f, "\\{byte:03}")?,
+                        }
+                    }
+                    f.write_str("\"")?;
+                }
+                Ok(())
+            }
+            RecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{priority} {weight} {port} {target}"),
+            RecordData::Soa {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{mname} {rname} {serial} {refresh} {retry} {expire} {minimum}"
+            ),
+            RecordData::Other(data) => {
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    f.write_str(" ")?;
+                    for byte in data {
+                        write!(f, "{byte:02X}")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A resource record; names end with a dot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub rtype: RecordType,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+impl Record {
+    /// `IN`, or `CLASS` and the number for the rarely used others
+    pub fn class_name(&self) -> String {
+        match self.class {
+            CLASS_IN => "IN".to_string(),
+            3 => "CH".to_string(),
+            4 => "HS".to_string(),
+            class => format!("CLASS{class}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub rtype: RecordType,
+    pub class: u16,
+}
+
+/// A decoded answer message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub id: u16,
+    /// The header's second word: QR, opcode, AA, TC, RD, RA, AD, CD and the
+    /// response code
+    pub flags: u16,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authority: Vec<Record>,
+    pub additional: Vec<Record>,
+}
+
+impl Response {
+    pub fn truncated(&self) -> bool {
+        self.flags & 0x0200 != 0
+    }
+
+    /// The response code's name
+    pub fn status(&self) -> String {
+        match self.flags & 0x000f {
+            0 => "NOERROR".to_string(),
+            1 => "FORMERR".to_string(),
+            2 => "SERVFAIL".to_string(),
+            3 => "NXDOMAIN".to_string(),
+            4 => "NOTIMP".to_string(),
+            5 => "REFUSED".to_string(),
+            code => format!("RESERVED{code}"),
+        }
+    }
+
+    /// The header bits set, by their `dig` names
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        [
+            (0x8000, "qr"),
+            (0x0400, "aa"),
+            (0x0200, "tc"),
+            (0x0100, "rd"),
+            (0x0080, "ra"),
+            (0x0020, "ad"),
+            (0x0010, "cd"),
+        ]
+        .into_iter()
+        .filter(|&(bit, _)| self.flags & bit != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// A query for `name` asking for recursion
+pub fn encode_query(id: u16, name: &str, rtype: RecordType) -> Result<Vec<u8>, String> {
+    let mut message = Vec::with_capacity(512);
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question, no records
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    encode_name(&mut message, name)?;
+    message.extend_from_slice(&rtype.0.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+fn encode_name(out: &mut Vec<u8>, name: &str) -> Result<(), String> {
+    let illegal = || format!("'{name}' is not a legal name");
+    let start = out.len();
+    let relative = name.strip_suffix('.').unwrap_or(name);
+    if !relative.is_empty() {
+        for label in relative.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(illegal());
+            }
+            out.push(label.len() as u8);
+            out.extend_from_slice(label.as_bytes());
+        }
+    }
+    out.push(0);
+    if out.len() - start > 255 {
+        return Err(illegal());
+    }
+    Ok(())
+}
+
+/// Read a whole message
+pub fn decode_response(message: &[u8]) -> Result<Response, String> {
+    let mut reader = Reader { message, at: 0 };
+    let id = reader.u16()?;
+    let flags = reader.u16()?;
+    let counts = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+    let mut questions = Vec::new();
+    for _ in 0..counts[0] {
+        questions.push(Question {
+            name: reader.name()?,
+            rtype: RecordType(reader.u16()?),
+            class: reader.u16()?,
+        });
+    }
+    let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+    for (section, &count) in sections.iter_mut().zip(&counts[1..]) {
+        for _ in 0..count {
+            section.push(reader.record()?);
+        }
+    }
+    let [answers, authority, additional] = sections;
+    Ok(Response {
+        id,
+        flags,
+        questions,
+        answers,
+        authority,
+        additional,
+    })
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .message
+            .get(self.at..self.at + count)
+            .ok_or_else(|| "message is truncated".to_string())?;
+        self.at += count;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a name, following compression pointers
+    fn name(&mut self) -> Result<String, String> {
+        let truncated = || "message is truncated".to_string();
+        let mut name = String::new();
+        let mut at = self.at;
+        let mut jumps = 0;
+        loop {
+            let length = *self.message.get(at).ok_or_else(truncated)? as usize;
+            match length & 0xc0 {
+                0xc0 => {
+                    let low = *self.message.get(at + 1).ok_or_else(truncated)? as usize;
+                    if jumps == 0 {
+                        self.at = at + 2;
+                    }
+                    jumps += 1;
+                    // Every pointer leads to an earlier name, so more jumps
+                    // than the message has room for means a loop
+                    if jumps > self.message.len() / 2 {
+                        return Err("name compression loops".to_string());
+                    }
+                    at = ((length & 0x3f) << 8) | low;
+                }
+                0 if length == 0 => {
+                    if jumps == 0 {
+                        self.at = at + 1;
+                    }
+                    break;
+                }
+                0 => {
+                    let label = self
+                        .message
+                        .get(at + 1..at + 1 + length)
+                        .ok_or_else(truncated)?;
+                    for &byte in label {
+                        match byte {
+                            b'.' | b'\\' => {
+                                name.push('\\');
+                                name.push(byte as char);
+                            }
+                            b'!'..=b'~' => name.push(byte as char),
+                            _ => name.push_str(&format!("\\{byte:03}")),
+                        }
+                    }
+                    name.push('.');
+                    at += 1 + length;
+                }
+                _ => return Err("unknown label type".to_string()),
+            }
+        }
+        if name.is_empty() {
+            name.push('.');
+        }
+        Ok(name)
+    }
+
+    fn record(&mut self) -> Result<Record, String> {
+        let name = self.name()?;
+        let rtype = RecordType(self.u16()?);
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let length = self.u16()? as usize;
+        let end = self.at + length;
+        if end > self.message.len() {
+            return Err("message is truncated".to_string());
+        }
+        let data = match rtype {
+            RecordType::A if length == 4 => {
+                let bytes = self.bytes(4)?;
+                RecordData::A(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+            }
+            RecordType::AAAA if length == 16 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(self.bytes(16)?);
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            RecordType::NS | RecordType::CNAME | RecordType::PTR => RecordData::Name(self.name()?),
+            RecordType::MX => RecordData::Mx {
+                preference: self.u16()?,
+                exchange: self.name()?,
+            },
+            RecordType::TXT => {
+                let mut strings = Vec::new();
+                while self.at < end {
+                    let size = self.bytes(1)?[0] as usize;
+                    strings.push(self.bytes(size)?.to_vec());
+                }
+                RecordData::Txt(strings)
+            }
+            RecordType::SRV => RecordData::Srv {
+                priority: self.u16()?,
+                weight: self.u16()?,
+                port: self.u16()?,
+                target: self.name()?,
+            },
+            RecordType::SOA => RecordData::Soa {
+                mname: self.name()?,
+                rname: self.name()?,
+                serial: self.u32()?,
+                refresh: self.u32()?,
+                retry: self.u32()?,
+                expire: self.u32()?,
+                minimum: self.u32()?,
+            },
+            _ => RecordData::Other(self.bytes(length)?.to_vec()),
+        };
+        if self.at != end {
+            return Err(format!("{rtype} record has the wrong length"));
+        }
+        Ok(Record {
+            name,
+            rtype,
+            class,
+            ttl,
+            data,
+        })
+    }
+}
+
+/// How queries are sent
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// How long to wait for each answer
+    pub timeout: Duration,
+    /// How many times a UDP query is sent before giving up
+    pub tries: u32,
+    /// Whether to use TCP from the start
+    pub tcp: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            timeout: Duration::from_secs(5),
+            tries: 3,
+            tcp: false,
+        }
+    }
+}
+
+/// An answer and how it came
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub response: Response,
+    pub server: SocketAddr,
+    /// Whether it came over TCP
+    pub tcp: bool,
+    /// The message's length in bytes
+    pub size: usize,
+    pub elapsed: Duration,
+}
+
+/// Ask `server` for the `rtype` records of `name`
+pub fn query(
+    server: SocketAddr,
+    name: &str,
+    rtype: RecordType,
+    options: &QueryOptions,
+) -> io::Result<Reply> {
+    let id = OsRng.next_u32() as u16;
+    let message = encode_query(id, name, rtype)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let started = Instant::now();
+    if !options.tcp {
+        let (response, size) = query_udp(server, id, &message, options)?;
+        if !response.truncated() {
+            return Ok(Reply {
+                response,
+                server,
+                tcp: false,
+                size,
+                elapsed: started.elapsed(),
+            });
+        }
+    }
+    let (response, size) = query_tcp(server, id, &message, options.timeout)?;
+    Ok(Reply {
+        response,
+        server,
+        tcp: true,
+        size,
+        elapsed: started.elapsed(),
+    })
+}
+
+fn query_udp(
+    server: SocketAddr,
+    id: u16,
+    message: &[u8],
+    options: &QueryOptions,
+) -> io::Result<(Response, usize)> {
+    let local = if server.is_ipv6() {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+    let mut buffer = vec![0; 65_535];
+    for _ in 0..options.tries.max(1) {
+        socket.send(message)?;
+        let deadline = Instant::now() + options.timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(left))?;
+            match socket.recv(&mut buffer) {
+                // Late answers to an earlier try and anything unreadable are
+                // passed over
+                Ok(size) => match decode_response(&buffer[..size]) {
+                    Ok(response) if response.id == id => return Ok((response, size)),
+                    _ => {}
+                },
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "timed out; no servers could be reached",
+    ))
+}
+
+fn query_tcp(
+    server: SocketAddr,
+    id: u16,
+    message: &[u8],
+    timeout: Duration,
+) -> io::Result<(Response, usize)> {
+    let mut stream = TcpStream::connect_timeout(&server, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    // Each message goes with its length in front
+    let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)?;
+    let mut length = [0; 2];
+    stream.read_exact(&mut length)?;
+    let mut reply = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut reply)?;
+    let response =
+        decode_response(&reply).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if response.id != id {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "reply does not match the query",
+        ));
+    }
+    Ok((response, reply.len()))
+}
+
+/// The name a PTR query for `address` asks about
+pub fn reverse_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        }
+        IpAddr::V6(address) => {
+            let mut name = String::new();
+            for byte in address.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0xf, byte >> 4));
+            }
+            name + "ip6.arpa."
+        }
+    }
+}
+
+/// The name servers the system is set up to use
+pub fn system_servers() -> Vec<IpAddr> {
+    #[cfg(unix)]
+    {
+        std::fs::read_to_string("/etc/resolv.conf")
+            .map(|conf| parse_resolv_conf(&conf))
+            .unwrap_or_default()
+    }
+    #[cfg(windows)]
+    {
+        windows_servers()
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Vec::new()
+    }
+}
+
+/// The `nameserver` lines of a resolv.conf, without the zone of a
+/// link-local address, which `SocketAddr` has no text form for
+#[cfg(unix)]
+fn parse_resolv_conf(conf: &str) -> Vec<IpAddr> {
+    conf.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next()? != "nameserver" {
+                return None;
+            }
+            let address = words.next()?;
+            address.split('%').next()?.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn windows_servers() -> Vec<IpAddr> {
+    use windows_sys::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, NO_ERROR};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetNetworkParams, FIXED_INFO_W2KSP1, IP_ADDR_STRING,
+    };
+
+    // u64s keep the structure aligned
+    let mut buffer: Vec<u64> = Vec::new();
+    let mut size = 0u32;
+    loop {
+        // SAFETY: the buffer is at least as large as `size` says
+        match unsafe { GetNetworkParams(buffer.as_mut_ptr().cast(), &mut size) } {
+            NO_ERROR => break,
+            ERROR_BUFFER_OVERFLOW => buffer = vec![0; (size as usize).div_ceil(8)],
+            _ => return Vec::new(),
+        }
+    }
+    let mut servers = Vec::new();
+    // SAFETY: GetNetworkParams filled the buffer with a FIXED_INFO whose
+    // server list links to entries inside the same buffer
+    unsafe {
+        let info = &*buffer.as_ptr().cast::<FIXED_INFO_W2KSP1>();
+        let mut entry: *const IP_ADDR_STRING = &info.DnsServerList;
+        while let Some(server) = entry.as_ref() {
+            let text = std::ffi::CStr::from_ptr(server.IpAddress.String.as_ptr().cast());
+            if let Some(address) = text.to_str().ok().and_then(|text| text.parse().ok()) {
+                servers.push(address);
+            }
+            entry = server.Next;
+        }
+    }
+    servers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An answer to `encode_query(7, "example.com", MX)` with one MX record
+    /// whose exchange points back into the question, and a TXT record
+    fn mx_answer() -> Vec<u8> {
+        let mut message = encode_query(7, "example.com", RecordType::MX).unwrap();
+        message[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+        message[6..8].copy_from_slice(&2u16.to_be_bytes());
+        // example.com. MX 10 mail.example.com.
+        message.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 0, 0x0e, 0x10, 0, 9, 0, 10]);
+        message.extend_from_slice(&[4, b'm', b'a', b'i', b'l', 0xc0, 12]);
+        // example.com. TXT "v=1" "a\"b"
+        message.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 8]);
+        message.extend_from_slice(&[3, b'v', b'=', b'1', 3, b'a', b'"', b'b']);
+        message
+    }
+
+    #[test]
+    fn decodes_compressed_names_and_record_data() {
+        let response = decode_response(&mx_answer()).unwrap();
+        assert_eq!(response.id, 7);
+        assert_eq!(response.status(), "NOERROR");
+        assert_eq!(response.flag_names(), ["qr", "rd", "ra"]);
+        assert_eq!(response.questions[0].name, "example.com.");
+        assert_eq!(response.questions[0].rtype, RecordType::MX);
+
+        let mx = &response.answers[0];
+        assert_eq!((mx.name.as_str(), mx.ttl), ("example.com.", 3600));
+        assert_eq!(mx.data.to_string(), "10 mail.example.com.");
+        assert_eq!(response.answers[1].data.to_string(), "\"v=1\" \"a\\\"b\"");
+
+        let mut looping = mx_answer();
+        let at = looping.len() - 12 - 8;
+        looping[at..at + 2].copy_from_slice(&[0xc0, at as u8]);
+        assert!(decode_response(&looping).is_err());
+        assert!(decode_response(&mx_answer()[..40]).is_err());
+    }
+
+    #[test]
+    fn names_types_and_reverse_lookups() {
+        assert_eq!(RecordType::from_name("aaaa"), Some(RecordType::AAAA));
+        assert_eq!(RecordType::from_name("TYPE99"), Some(RecordType(99)));
+        assert_eq!(RecordType::from_name("example"), None);
+        assert_eq!(RecordType(99).to_string(), "TYPE99");
+        assert!(encode_query(1, &"a".repeat(64), RecordType::A).is_err());
+        assert!(encode_query(1, "a..b", RecordType::A).is_err());
+        assert_eq!(
+            reverse_name("192.0.2.1".parse().unwrap()),
+            "1.2.0.192.in-addr.arpa."
+        );
+        assert_eq!(
+            reverse_name("2001:db8::1".parse().unwrap()),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_resolv_conf_name_servers() {
+        let conf = "# comment\nsearch example.com\nnameserver 192.0.2.53\n\
+                    nameserver fe80::1%eth0\nnameserver bogus\n";
+        let servers: Vec<IpAddr> = vec!["192.0.2.53".parse().unwrap(), "fe80::1".parse().unwrap()];
+        assert_eq!(parse_resolv_conf(conf), servers);
+    }
+}
@@ -0,0 +1,747 @@
+//! A small HTTP/1.1 client
+//!
+//! Each request goes out on its own connection with `Connection: close`,
+//! and the reply's body is read by its length, in chunks or up to the end of
+//! the connection (RFC 9112), all at once by [`exchange`] or as it arrives
+//! from [`open`]. Requests go to the server, or through an HTTP
+//! proxy in absolute form. Only `http` URLs are spoken, as the shell has no
+//! TLS of its own.
+
+use super::io_message;
+use base64::{engine::general_purpose, Engine as _};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use url::{Position, Url};
+
+/// A request to send
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub url: Url,
+    /// Headers in the order they are sent; Host, Content-Length and
+    /// Connection are added unless they are among them
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Speak HTTP/1.0 rather than HTTP/1.1
+    pub http10: bool,
+}
+
+/// A reply, after any interim 1xx ones
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// `HTTP/1.1` or `HTTP/1.0`
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The address connected to, the proxy's when there is one
+    pub peer: SocketAddr,
+}
+
+impl Response {
+    /// The first header of that name, in any case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The status line and headers as they came, with the blank line after
+    pub fn head(&self) -> String {
+        let mut head = format!("{} {} {}\r\n", self.version, self.status, self.reason);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        head
+    }
+}
+
+/// An HTTP proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub host: String,
+    pub port: u16,
+    /// `user:password` for Proxy-Authorization
+    pub credentials: Option<String>,
+}
+
+impl Proxy {
+    /// A proxy given as `[http://][user:password@]host[:port]`, port 1080
+    /// when none is given as curl has it
+    pub fn parse(text: &str) -> Result<Proxy, String> {
+        let full = if text.contains("://") {
+            text.to_string()
+        } else {
+            format!("http://{text}")
+        };
+        let url = Url::parse(&full).map_err(|_| format!("invalid proxy '{text}'"))?;
+        if url.scheme() != "http" {
+            return Err(format!("unsupported proxy scheme '{}'", url.scheme()));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("invalid proxy '{text}'"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let credentials = (!url.username().is_empty()).then(|| {
+            format!(
+                "{}:{}",
+                decode(url.username()),
+                decode(url.password().unwrap_or(""))
+            )
+        });
+        Ok(Proxy {
+            host,
+            port: url.port().unwrap_or(1080),
+            credentials,
+        })
+    }
+
+    /// The proxy `http_proxy` or `all_proxy` names for `url`, as `var`
+    /// looks them up, unless `no_proxy` (or `no_proxy_override`) lists its
+    /// host. Of `http_proxy` only the lower case name is read, as curl does,
+    /// since a CGI program gets `HTTP_PROXY` from a request's header.
+    pub fn from_env(
+        url: &Url,
+        no_proxy_override: Option<&str>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Option<Proxy>, String> {
+        let first = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        };
+        let no_proxy = match no_proxy_override {
+            Some(list) => Some(list.to_string()),
+            None => first(&["no_proxy", "NO_PROXY"]),
+        };
+        let host = url.host_str().unwrap_or_default();
+        if no_proxy.is_some_and(|list| bypasses(&list, host)) {
+            return Ok(None);
+        }
+        match first(&["http_proxy", "all_proxy", "ALL_PROXY"]) {
+            Some(proxy) => Proxy::parse(&proxy).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Whether the `no_proxy` list, of host names and domains separated by
+/// commas or `*` for all, takes in `host`
+pub fn bypasses(list: &str, host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    list.split(',')
+        .map(|entry| entry.trim().trim_start_matches('.'))
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            entry == "*"
+                || host.eq_ignore_ascii_case(entry)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+        })
+}
+
+/// Where and for how long to connect
+#[derive(Debug, Clone, Default)]
+pub struct Transport {
+    pub proxy: Option<Proxy>,
+    pub connect_timeout: Option<Duration>,
+    /// When the whole exchange must be over
+    pub deadline: Option<Instant>,
+    /// How long a single read or write may wait
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Why an exchange failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Resolve(String),
+    ResolveProxy(String),
+    Connect {
+        host: String,
+        port: u16,
+        reason: String,
+        refused: bool,
+    },
+    /// The deadline passed, after that long
+    Timeout(Duration),
+    Send(String),
+    Receive(String),
+    /// The body ended with that many bytes of its length missing
+    Partial(u64),
+    BadReply(String),
+}
+
+impl Error {
+    /// curl's exit status for the failure
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::ResolveProxy(_) => 5,
+            Error::Resolve(_) => 6,
+            Error::Connect { .. } => 7,
+            Error::BadReply(_) => 8,
+            Error::Partial(_) => 18,
+            Error::Timeout(_) => 28,
+            Error::Send(_) => 55,
+            Error::Receive(_) => 56,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Resolve(host) => write!(f, "Could not resolve host: {host}"),
+            Error::ResolveProxy(host) => write!(f, "Could not resolve proxy: {host}"),
+            Error::Connect {
+                host, port, reason, ..
+            } => write!(f, "Failed to connect to {host} port {port}: {reason}"),
+            Error::Timeout(after) => {
+                write!(
+                    f,
+                    "Operation timed out after {} milliseconds",
+                    after.as_millis()
+                )
+            }
+            Error::Send(reason) => write!(f, "Send failure: {reason}"),
+            Error::Receive(reason) => write!(f, "Recv failure: {reason}"),
+            Error::Partial(left) => {
+                write!(f, "transfer closed with {left} bytes remaining to read")
+            }
+            Error::BadReply(what) => write!(f, "Weird server reply: {what}"),
+        }
+    }
+}
+
+/// The request line and headers `exchange` sends for `request`, with the
+/// blank line after them
+pub fn request_head(request: &Request, transport: &Transport) -> String {
+    let url = &request.url;
+    let target = if transport.proxy.is_some() {
+        url[..Position::AfterQuery].to_string()
+    } else {
+        url[Position::BeforePath..Position::AfterQuery].to_string()
+    };
+    let version = if request.http10 { "1.0" } else { "1.1" };
+    let mut head = format!("{} {target} HTTP/{version}\r\n", request.method);
+    let has = |name: &str| {
+        request
+            .headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case(name))
+    };
+    if !has("host") {
+        let host = url.host_str().unwrap_or_default();
+        match url.port() {
+            Some(port) => head.push_str(&format!("Host: {host}:{port}\r\n")),
+            None => head.push_str(&format!("Host: {host}\r\n")),
+        }
+    }
+    if let Some(credentials) = transport
+        .proxy
+        .as_ref()
+        .and_then(|proxy| proxy.credentials.as_deref())
+    {
+        head.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            general_purpose::STANDARD.encode(credentials)
+        ));
+    }
+    for (name, value) in &request.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    let sends_body = !request.body.is_empty() || matches!(request.method.as_str(), "POST" | "PUT");
+    if sends_body && !has("content-length") {
+        head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+    }
+    if !has("connection") {
+        head.push_str("Connection: close\r\n");
+    }
+    head.push_str("\r\n");
+    head
+}
+
+/// Send `request` and read the reply
+pub fn exchange(request: &Request, transport: &Transport) -> Result<Response, Error> {
+    let (mut response, mut body) = open(request, transport)?;
+    body.read_to_end(&mut response.body)
+        .map_err(|e| body.fail(e))?;
+    Ok(response)
+}
+
+/// Send `request` and read the reply's head, leaving its body to be read
+/// as it arrives; the response's own `body` stays empty
+pub fn open(request: &Request, transport: &Transport) -> Result<(Response, Body), Error> {
+    let started = Instant::now();
+    let timeout = || Error::Timeout(started.elapsed());
+    let (host, port, proxied) = match &transport.proxy {
+        Some(proxy) => (proxy.host.clone(), proxy.port, true),
+        None => {
+            let host = request.url.host_str().unwrap_or_default();
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let port = request.url.port_or_known_default().unwrap_or(80);
+            (host.to_string(), port, false)
+        }
+    };
+    let addrs: Vec<SocketAddr> = match (host.as_str(), port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(_) => Vec::new(),
+    };
+    if addrs.is_empty() {
+        return Err(if proxied {
+            Error::ResolveProxy(host)
+        } else {
+            Error::Resolve(host)
+        });
+    }
+
+    let mut last = None;
+    let mut stream = None;
+    for addr in &addrs {
+        let limit = match (transport.connect_timeout, remaining(transport.deadline)) {
+            (_, Some(left)) if left.is_zero() => return Err(timeout()),
+            (Some(connect), Some(left)) => Some(connect.min(left)),
+            (connect, left) => connect.or(left),
+        };
+        let attempt = match limit {
+            Some(limit) => TcpStream::connect_timeout(addr, limit),
+            None => TcpStream::connect(addr),
+        };
+        match attempt {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(e) => last = Some(e),
+        }
+    }
+    let stream = match stream {
+        Some(stream) => stream,
+        None => {
+            let e = last.unwrap_or_else(|| io::ErrorKind::NotConnected.into());
+            if e.kind() == io::ErrorKind::TimedOut {
+                return Err(timeout());
+            }
+            return Err(Error::Connect {
+                host,
+                port,
+                reason: io_message(&e),
+                refused: e.kind() == io::ErrorKind::ConnectionRefused,
+            });
+        }
+    };
+    let peer = stream.peer_addr().unwrap_or(addrs[0]);
+
+    let mut conn = Conn {
+        stream,
+        deadline: transport.deadline,
+        idle: transport.idle_timeout,
+    };
+    let mut message = request_head(request, transport).into_bytes();
+    message.extend_from_slice(&request.body);
+    conn.write_all(&message).map_err(|e| match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => timeout(),
+        _ => Error::Send(io_message(&e)),
+    })?;
+
+    let mut body = Body {
+        reader: Box::new(BufReader::new(conn)),
+        framing: Framing::Empty,
+        started,
+    };
+    // Interim replies like 100 Continue come before the real one
+    let (version, status, phrase, headers) = loop {
+        let head = read_head(&mut body.reader).map_err(|e| body.fail(e))?;
+        let head = head.ok_or_else(|| Error::BadReply("empty reply from server".to_string()))?;
+        if !(100..200).contains(&head.1) || head.1 == 101 {
+            break head;
+        }
+    };
+    let response = Response {
+        version,
+        status,
+        reason: phrase,
+        headers,
+        body: Vec::new(),
+        peer,
+    };
+    let bodiless = request.method == "HEAD" || matches!(status, 100..=199 | 204 | 304);
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|coding| coding.to_ascii_lowercase().contains("chunked"));
+    body.framing = if bodiless {
+        Framing::Empty
+    } else if chunked {
+        Framing::Chunked {
+            left: 0,
+            done: false,
+        }
+    } else if let Some(length) = response.header("content-length") {
+        let length: u64 = length
+            .trim()
+            .parse()
+            .map_err(|_| Error::BadReply(format!("invalid Content-Length '{length}'")))?;
+        Framing::Length(length)
+    } else {
+        Framing::Close
+    };
+    Ok((response, body))
+}
+
+/// The body of a reply, read as it arrives
+pub struct Body {
+    reader: Box<dyn BufRead + Send>,
+    framing: Framing,
+    started: Instant,
+}
+
+/// How the end of a body is told
+enum Framing {
+    Empty,
+    /// By its length, with that many bytes still to come
+    Length(u64),
+    /// In chunks, with that many bytes of the current one to come
+    Chunked {
+        left: u64,
+        done: bool,
+    },
+    /// By the connection closing
+    Close,
+}
+
+impl Body {
+    /// The bytes still to come, when the reply gave its length
+    pub fn remaining(&self) -> Option<u64> {
+        match self.framing {
+            Framing::Empty => Some(0),
+            Framing::Length(left) => Some(left),
+            _ => None,
+        }
+    }
+
+    /// The error an exchange reports for a failed read of the body
+    pub fn fail(&self, e: io::Error) -> Error {
+        match (e.kind(), &self.framing) {
+            (io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock, _) => {
+                Error::Timeout(self.started.elapsed())
+            }
+            (io::ErrorKind::InvalidData, _) => Error::BadReply(e.to_string()),
+            (io::ErrorKind::UnexpectedEof, Framing::Length(left)) => Error::Partial(*left),
+            _ => Error::Receive(io_message(&e)),
+        }
+    }
+
+    /// Start the next chunk, returning false after the last
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        let mut line = String::new();
+        if read_line(&mut self.reader, &mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = u64::from_str_radix(size.trim(), 16).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid chunk size '{size}'"),
+            )
+        })?;
+        if size > 0 {
+            self.framing = Framing::Chunked {
+                left: size,
+                done: false,
+            };
+            return Ok(true);
+        }
+        // The trailer ends at a blank line
+        loop {
+            line.clear();
+            if read_line(&mut self.reader, &mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+        }
+        self.framing = Framing::Chunked {
+            left: 0,
+            done: true,
+        };
+        Ok(false)
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.framing {
+            Framing::Empty | Framing::Length(0) => Ok(0),
+            Framing::Length(left) => {
+                let want = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+                let n = self.reader.read(&mut buf[..want])?;
+                if n == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.framing = Framing::Length(left - n as u64);
+                Ok(n)
+            }
+            Framing::Chunked { done: true, .. } => Ok(0),
+            Framing::Chunked { left: 0, .. } => {
+                if !self.next_chunk()? {
+                    return Ok(0);
+                }
+                self.read(buf)
+            }
+            Framing::Chunked { left, .. } => {
+                let want = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+                let n = self.reader.read(&mut buf[..want])?;
+                if n == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                let left = left - n as u64;
+                if left == 0 {
+                    // The CRLF after the chunk's data
+                    let mut line = String::new();
+                    read_line(&mut self.reader, &mut line)?;
+                }
+                self.framing = Framing::Chunked { left, done: false };
+                Ok(n)
+            }
+            Framing::Close => self.reader.read(buf),
+        }
+    }
+}
+
+/// How long until `deadline`, if there is one
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// A connection whose reads and writes give up at the deadline, or after
+/// waiting idle for too long
+struct Conn {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+    idle: Option<Duration>,
+}
+
+impl Conn {
+    fn arm(&self) -> io::Result<()> {
+        let limit = match (remaining(self.deadline), self.idle) {
+            (Some(left), _) if left.is_zero() => return Err(io::ErrorKind::TimedOut.into()),
+            (Some(left), Some(idle)) => Some(left.min(idle)),
+            (left, idle) => left.or(idle),
+        };
+        match limit {
+            Some(limit) => {
+                self.stream.set_read_timeout(Some(limit))?;
+                self.stream.set_write_timeout(Some(limit))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for Conn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.arm()?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Conn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.arm()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+type Head = (String, u16, String, Vec<(String, String)>);
+
+/// A status line and its headers, or `None` when the connection closed
+/// before any
+fn read_head(reader: &mut impl BufRead) -> io::Result<Option<Head>> {
+    let mut line = String::new();
+    if read_line(reader, &mut line)? == 0 {
+        return Ok(None);
+    }
+    let status_line = line.trim_end().to_string();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default().to_string();
+    let status = parts.next().and_then(|code| code.parse::<u16>().ok());
+    let status = match status {
+        Some(status) if version.starts_with("HTTP/") => status,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid status line '{status_line}'"),
+            ))
+        }
+    };
+    let reason = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if read_line(reader, &mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(Some((version, status, reason, headers)))
+}
+
+/// A line, with bytes that are not UTF-8 replaced
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let n = reader.read_until(b'\n', &mut bytes)?;
+    line.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(n)
+}
+
+/// Undo the percent encoding of a URL's user name or password
+pub fn decode(text: &str) -> String {
+    url::form_urlencoded::parse(format!("x={}", text.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn request(method: &str, url: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            url: Url::parse(url).unwrap(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            http10: false,
+        }
+    }
+
+    #[test]
+    fn writes_the_head_direct_and_through_a_proxy() {
+        let mut post = request("POST", "http://example.com:8080/a/b?x=1#top");
+        post.headers.push(("Accept".to_string(), "*/*".to_string()));
+        post.body = b"k=v".to_vec();
+        assert_eq!(
+            request_head(&post, &Transport::default()),
+            "POST /a/b?x=1 HTTP/1.1\r\nHost: example.com:8080\r\nAccept: */*\r\n\
+             Content-Length: 3\r\nConnection: close\r\n\r\n"
+        );
+
+        let transport = Transport {
+            proxy: Some(Proxy::parse("user:p%40ss@proxy:3128").unwrap()),
+            ..Transport::default()
+        };
+        assert_eq!(
+            request_head(&request("GET", "http://example.com/"), &transport),
+            "GET http://example.com/ HTTP/1.1\r\nHost: example.com\r\n\
+             Proxy-Authorization: Basic dXNlcjpwQHNz\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn reads_chunked_bodies_and_heads() {
+        let reader = Cursor::new(b"4;ext\r\nWiki\r\n5\r\npedia\r\n0\r\nX-T: 1\r\n\r\n".to_vec());
+        let mut body = Body {
+            reader: Box::new(reader),
+            framing: Framing::Chunked {
+                left: 0,
+                done: false,
+            },
+            started: Instant::now(),
+        };
+        let mut text = Vec::new();
+        body.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"Wikipedia");
+
+        let mut reader = Cursor::new(b"HTTP/1.1 404 Not Found\r\nA: b\r\nC:d\r\n\r\nrest".to_vec());
+        let (version, status, reason, headers) = read_head(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            (version.as_str(), status, reason.as_str()),
+            ("HTTP/1.1", 404, "Not Found")
+        );
+        assert_eq!(
+            headers,
+            vec![
+                ("A".to_string(), "b".to_string()),
+                ("C".to_string(), "d".to_string())
+            ]
+        );
+        assert!(read_head(&mut Cursor::new(b"SSH-2.0\r\n".to_vec())).is_err());
+        assert!(read_head(&mut Cursor::new(Vec::new())).unwrap().is_none());
+    }
+
+    #[test]
+    fn chooses_proxies_from_the_environment() {
+        let url = Url::parse("http://api.example.com/").unwrap();
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let proxy = Proxy::from_env(&url, None, env(&[("http_proxy", "http://p:8080")])).unwrap();
+        assert_eq!(
+            proxy.map(|p| (p.host, p.port)),
+            Some(("p".to_string(), 8080))
+        );
+        assert_eq!(
+            Proxy::from_env(&url, None, env(&[("HTTP_PROXY", "p:8080")])).unwrap(),
+            None
+        );
+        let vars = env(&[("all_proxy", "p"), ("no_proxy", "localhost,.example.com")]);
+        assert_eq!(Proxy::from_env(&url, None, vars).unwrap(), None);
+        let proxy = Proxy::from_env(&url, Some(""), env(&[("ALL_PROXY", "p")])).unwrap();
+        assert_eq!(proxy.map(|p| p.port), Some(1080));
+        assert!(bypasses("*", "anything"));
+        assert!(!bypasses("example.com", "notexample.com"));
+    }
+
+    #[test]
+    fn exchanges_with_a_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/path", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0];
+            while !head.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .unwrap();
+            String::from_utf8(head).unwrap()
+        });
+        let response = exchange(&request("GET", &url), &Transport::default()).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+        assert_eq!(response.header("CONTENT-LENGTH"), Some("5"));
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /path HTTP/1.1\r\nHost: 127.0.0.1:"));
+    }
+}
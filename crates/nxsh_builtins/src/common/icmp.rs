@@ -0,0 +1,949 @@
+//! ICMP probes for `ping` and `traceroute`
+//!
+//! On Unix the probes go out on a datagram ICMP socket where the system lets
+//! unprivileged users open one, and on a raw socket otherwise. A raw socket
+//! sees every ICMP message that arrives, so the time exceeded and
+//! unreachable reports of the routers on the way come in like echo replies;
+//! a datagram socket on Linux reads them from its error queue instead. UDP
+//! probes, which only ever draw such reports, are sent on Linux alone. On
+//! Windows echo requests are sent through the IP helper's ICMP functions.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::time::Duration;
+
+/// ICMP types of the messages sent and understood
+#[cfg(unix)]
+const ECHO_REQUEST_V4: u8 = 8;
+#[cfg(unix)]
+const ECHO_REPLY_V4: u8 = 0;
+#[cfg(unix)]
+const UNREACHABLE_V4: u8 = 3;
+#[cfg(unix)]
+const TIME_EXCEEDED_V4: u8 = 11;
+#[cfg(unix)]
+const ECHO_REQUEST_V6: u8 = 128;
+#[cfg(unix)]
+const ECHO_REPLY_V6: u8 = 129;
+#[cfg(unix)]
+const UNREACHABLE_V6: u8 = 1;
+#[cfg(unix)]
+const TIME_EXCEEDED_V6: u8 = 3;
+
+/// How often a wait for a reply asks whether to stop
+#[cfg(unix)]
+const TICK: Duration = Duration::from_millis(50);
+
+/// Which addresses a host name may resolve to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Family {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+/// The address probes to `host` go to: `host` itself when it is an address,
+/// or the first address of `family` it resolves to
+pub fn resolve(host: &str, family: Family) -> Result<IpAddr, String> {
+    let addresses = (host, 0)
+        .to_socket_addrs()
+        .map_err(|_| format!("{host}: Name or service not known"))?;
+    addresses
+        .map(|address| address.ip())
+        .find(|address| match family {
+            Family::Any => true,
+            Family::V4 => address.is_ipv4(),
+            Family::V6 => address.is_ipv6(),
+        })
+        .ok_or_else(|| format!("{host}: Address family for hostname not supported"))
+}
+
+/// How a probe is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeKind {
+    /// An ICMP echo request
+    Echo,
+    /// A UDP datagram to `port` plus the probe's sequence number, which the
+    /// target answers with a port unreachable report
+    Udp { port: u16 },
+}
+
+/// Why a destination was reported unreachable, in the terms ICMP and
+/// ICMPv6 share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unreachable {
+    Network,
+    Host,
+    Protocol,
+    Port,
+    /// Administratively prohibited, by a filter on the way
+    Prohibited,
+    /// Any other code of the family's unreachable message
+    Other(u8),
+}
+
+impl Unreachable {
+    pub fn from_v4(code: u8) -> Self {
+        match code {
+            0 => Unreachable::Network,
+            1 => Unreachable::Host,
+            2 => Unreachable::Protocol,
+            3 => Unreachable::Port,
+            9 | 10 | 13 => Unreachable::Prohibited,
+            code => Unreachable::Other(code),
+        }
+    }
+
+    pub fn from_v6(code: u8) -> Self {
+        match code {
+            0 => Unreachable::Network,
+            1 => Unreachable::Prohibited,
+            3 => Unreachable::Host,
+            4 => Unreachable::Port,
+            code => Unreachable::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unreachable::Network => f.write_str("Destination Net Unreachable"),
+            Unreachable::Host => f.write_str("Destination Host Unreachable"),
+            Unreachable::Protocol => f.write_str("Destination Protocol Unreachable"),
+            Unreachable::Port => f.write_str("Destination Port Unreachable"),
+            Unreachable::Prohibited => f.write_str("Communication administratively prohibited"),
+            Unreachable::Other(code) => write!(f, "Destination Unreachable, Bad Code: {code}"),
+        }
+    }
+}
+
+/// What answered a probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyKind {
+    /// The target itself, with an echo reply or data back on a UDP probe
+    Echo,
+    /// A router that dropped the probe when its time to live ran out
+    TimeExceeded,
+    /// The target or a router reporting the target unreachable
+    Unreachable(Unreachable),
+}
+
+impl fmt::Display for ReplyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplyKind::Echo => f.write_str("Echo Reply"),
+            ReplyKind::TimeExceeded => f.write_str("Time to live exceeded"),
+            ReplyKind::Unreachable(reason) => reason.fmt(f),
+        }
+    }
+}
+
+/// The answer to a probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub kind: ReplyKind,
+    /// Who sent it
+    pub from: IpAddr,
+    /// The length of the ICMP message or UDP data that came back
+    pub size: usize,
+    /// The time to live or hop limit it arrived with, where the system tells
+    pub ttl: Option<u8>,
+    pub rtt: Duration,
+}
+
+/// A socket sending probes to one target
+pub struct Prober {
+    target: IpAddr,
+    #[cfg(unix)]
+    kind: ProbeKind,
+    /// The identifier of the echo requests, or None where the kernel sets it
+    #[cfg(unix)]
+    ident: Option<u16>,
+    #[cfg(unix)]
+    socket: socket2::Socket,
+    #[cfg(unix)]
+    buffer: Vec<u8>,
+    #[cfg(windows)]
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+#[cfg(unix)]
+impl Prober {
+    /// Open a socket for probes of `kind` to `target`
+    pub fn open(target: IpAddr, kind: ProbeKind) -> io::Result<Self> {
+        use rand::{rngs::OsRng, RngCore};
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let domain = if target.is_ipv6() {
+            Domain::IPV6
+        } else {
+            Domain::IPV4
+        };
+        let (socket, datagram) = match kind {
+            ProbeKind::Echo => {
+                let protocol = if target.is_ipv6() {
+                    Protocol::ICMPV6
+                } else {
+                    Protocol::ICMPV4
+                };
+                match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+                    Ok(socket) => (socket, true),
+                    // socket2 keeps its SOCK_RAW constant behind a feature
+                    Err(_) => (
+                        Socket::new(domain, Type::from(libc::SOCK_RAW), Some(protocol))?,
+                        false,
+                    ),
+                }
+            }
+            ProbeKind::Udp { .. } if cfg!(target_os = "linux") => {
+                (Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?, true)
+            }
+            ProbeKind::Udp { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "UDP probes are only supported on Linux",
+                ))
+            }
+        };
+        #[cfg(target_os = "linux")]
+        {
+            let (level, recverr, recvttl) = if target.is_ipv6() {
+                (libc::SOL_IPV6, libc::IPV6_RECVERR, libc::IPV6_RECVHOPLIMIT)
+            } else {
+                (libc::SOL_IP, libc::IP_RECVERR, libc::IP_RECVTTL)
+            };
+            if datagram {
+                enable(&socket, level, recverr)?;
+            }
+            enable(&socket, level, recvttl)?;
+        }
+        // The kernel sets the identifier of a Linux datagram socket's
+        // requests and only hands it the replies that carry it back
+        let ident = if datagram && cfg!(target_os = "linux") {
+            None
+        } else {
+            Some(OsRng.next_u32() as u16)
+        };
+        Ok(Prober {
+            target,
+            kind,
+            ident,
+            socket,
+            buffer: vec![0; 65536],
+        })
+    }
+
+    /// Send probe `seq` with `size` bytes of data and wait up to `timeout`
+    /// for its answer, or until `stop` says to give up. Other messages that
+    /// arrive meanwhile, such as late answers to earlier probes, are skipped.
+    pub fn probe(
+        &mut self,
+        seq: u16,
+        ttl: Option<u8>,
+        size: usize,
+        timeout: Duration,
+        stop: &mut dyn FnMut() -> bool,
+    ) -> io::Result<Option<Reply>> {
+        use std::net::SocketAddr;
+        use std::os::unix::io::AsRawFd;
+        use std::time::Instant;
+
+        let v6 = self.target.is_ipv6();
+        if let Some(ttl) = ttl {
+            if v6 {
+                self.socket.set_unicast_hops_v6(u32::from(ttl))?;
+            } else {
+                self.socket.set_ttl(u32::from(ttl))?;
+            }
+        }
+        let (packet, port) = match self.kind {
+            ProbeKind::Echo => (echo_request(v6, self.ident.unwrap_or(0), seq, size), 0),
+            ProbeKind::Udp { port } => (udp_payload(seq, size), port.wrapping_add(seq)),
+        };
+        let started = Instant::now();
+        self.socket
+            .send_to(&packet, &SocketAddr::new(self.target, port).into())?;
+        let deadline = started + timeout;
+        loop {
+            if stop() {
+                return Ok(None);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(None);
+            }
+            let events = poll(self.socket.as_raw_fd(), left.min(TICK))?;
+            if events & libc::POLLERR != 0 {
+                if let Some((kind, from)) = self.queued_error(seq)? {
+                    return Ok(Some(Reply {
+                        kind,
+                        from,
+                        size: 0,
+                        ttl: None,
+                        rtt: started.elapsed(),
+                    }));
+                }
+            }
+            if events & libc::POLLIN == 0 {
+                continue;
+            }
+            let received = match receive(self.socket.as_raw_fd(), &mut self.buffer, 0) {
+                Ok(received) => received,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            let message = &self.buffer[..received.len];
+            let answer = match self.kind {
+                ProbeKind::Echo => parse_reply(message, v6, self.ident, seq),
+                ProbeKind::Udp { .. } => Some((ReplyKind::Echo, message.len(), None)),
+            };
+            if let Some((kind, size, header_ttl)) = answer {
+                return Ok(Some(Reply {
+                    kind,
+                    from: received.from.unwrap_or(self.target),
+                    size,
+                    ttl: header_ttl.or(received.ttl),
+                    rtt: started.elapsed(),
+                }));
+            }
+        }
+    }
+
+    /// Read a report from the error queue, and make it the answer to probe
+    /// `seq` when the probe it quotes is that one
+    #[cfg(target_os = "linux")]
+    fn queued_error(&mut self, seq: u16) -> io::Result<Option<(ReplyKind, IpAddr)>> {
+        use std::os::unix::io::AsRawFd;
+
+        let received = match receive(
+            self.socket.as_raw_fd(),
+            &mut self.buffer,
+            libc::MSG_ERRQUEUE,
+        ) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Some(error) = received.error else {
+            return Ok(None);
+        };
+        let v6 = self.target.is_ipv6();
+        let origin = if v6 {
+            libc::SO_EE_ORIGIN_ICMP6
+        } else {
+            libc::SO_EE_ORIGIN_ICMP
+        };
+        if error.origin != origin {
+            return Ok(None);
+        }
+        let Some(kind) = error_kind(v6, error.icmp_type, error.code) else {
+            return Ok(None);
+        };
+        // The queue hands back what the probe carried: the whole echo
+        // request, or the data of the UDP datagram
+        let sent = &self.buffer[..received.len];
+        let ours = match self.kind {
+            ProbeKind::Echo => is_echo(sent, echo_types(v6).0, None, seq),
+            ProbeKind::Udp { .. } => sent.get(..2) == Some(&seq.to_be_bytes()[..]),
+        };
+        Ok(ours.then(|| (kind, error.offender.unwrap_or(self.target))))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn queued_error(&mut self, _seq: u16) -> io::Result<Option<(ReplyKind, IpAddr)>> {
+        Ok(None)
+    }
+}
+
+#[cfg(windows)]
+impl Prober {
+    /// Open an ICMP handle for probes of `kind` to `target`
+    pub fn open(target: IpAddr, kind: ProbeKind) -> io::Result<Self> {
+        use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+        use windows_sys::Win32::NetworkManagement::IpHelper::{Icmp6CreateFile, IcmpCreateFile};
+
+        if kind != ProbeKind::Echo {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "UDP probes are only supported on Linux",
+            ));
+        }
+        // SAFETY: both take no arguments and return a handle or
+        // INVALID_HANDLE_VALUE
+        let handle = unsafe {
+            if target.is_ipv6() {
+                Icmp6CreateFile()
+            } else {
+                IcmpCreateFile()
+            }
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Prober { target, handle })
+    }
+
+    /// Send probe `seq` with `size` bytes of data and wait up to `timeout`
+    /// for its answer. The wait cannot be cut short, so `stop` is only asked
+    /// before the probe goes out.
+    pub fn probe(
+        &mut self,
+        _seq: u16,
+        ttl: Option<u8>,
+        size: usize,
+        timeout: Duration,
+        stop: &mut dyn FnMut() -> bool,
+    ) -> io::Result<Option<Reply>> {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+        use std::time::Instant;
+        use std::{mem, ptr};
+        use windows_sys::Win32::Foundation::GetLastError;
+        use windows_sys::Win32::NetworkManagement::IpHelper::{
+            Icmp6SendEcho2, IcmpSendEcho, ICMPV6_ECHO_REPLY_LH, ICMP_ECHO_REPLY,
+            IP_OPTION_INFORMATION,
+        };
+        use windows_sys::Win32::Networking::WinSock::{AF_INET6, SOCKADDR_IN6};
+
+        // IP_STATUS values of the replies
+        const SUCCESS: u32 = 0;
+        const DEST_NET_UNREACHABLE: u32 = 11002;
+        const DEST_HOST_UNREACHABLE: u32 = 11003;
+        const DEST_PROT_UNREACHABLE: u32 = 11004;
+        const DEST_PORT_UNREACHABLE: u32 = 11005;
+        const REQ_TIMED_OUT: u32 = 11010;
+        const TTL_EXPIRED_TRANSIT: u32 = 11013;
+
+        if stop() {
+            return Ok(None);
+        }
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        let options = IP_OPTION_INFORMATION {
+            Ttl: ttl.unwrap_or(128),
+            Tos: 0,
+            Flags: 0,
+            OptionsSize: 0,
+            OptionsData: ptr::null_mut(),
+        };
+        // Room for the reply, the echoed data and an error quoting the probe;
+        // u64s keep the reply aligned
+        let mut buffer = vec![0u64; (mem::size_of::<ICMP_ECHO_REPLY>() + size + 256).div_ceil(8)];
+        let buffer_size = (buffer.len() * 8) as u32;
+        let millis = u32::try_from(timeout.as_millis())
+            .unwrap_or(u32::MAX)
+            .max(1);
+        let started = Instant::now();
+        let (status, from, reply_ttl, reply_size) = match self.target {
+            IpAddr::V4(address) => {
+                // SAFETY: the data, options and buffer outlive the call and
+                // are as large as given
+                let count = unsafe {
+                    IcmpSendEcho(
+                        self.handle,
+                        u32::from_ne_bytes(address.octets()),
+                        data.as_ptr().cast(),
+                        size as u16,
+                        &options,
+                        buffer.as_mut_ptr().cast(),
+                        buffer_size,
+                        millis,
+                    )
+                };
+                if count == 0 {
+                    // SAFETY: no arguments
+                    return match unsafe { GetLastError() } {
+                        REQ_TIMED_OUT => Ok(None),
+                        error => Err(io::Error::from_raw_os_error(error as i32)),
+                    };
+                }
+                // SAFETY: the buffer starts with the first reply
+                let reply = unsafe { &*buffer.as_ptr().cast::<ICMP_ECHO_REPLY>() };
+                (
+                    reply.Status,
+                    IpAddr::V4(Ipv4Addr::from(reply.Address.to_ne_bytes())),
+                    Some(reply.Options.Ttl),
+                    usize::from(reply.DataSize) + 8,
+                )
+            }
+            IpAddr::V6(address) => {
+                // SAFETY: an all zero socket address is valid
+                let mut source: SOCKADDR_IN6 = unsafe { mem::zeroed() };
+                source.sin6_family = AF_INET6;
+                let mut destination = source;
+                destination.sin6_addr.u.Byte = address.octets();
+                // SAFETY: as for IcmpSendEcho; without an event or routine
+                // the call waits for the reply
+                let count = unsafe {
+                    Icmp6SendEcho2(
+                        self.handle,
+                        ptr::null_mut(),
+                        None,
+                        ptr::null(),
+                        &source,
+                        &destination,
+                        data.as_ptr().cast(),
+                        size as u16,
+                        &options,
+                        buffer.as_mut_ptr().cast(),
+                        buffer_size,
+                        millis,
+                    )
+                };
+                if count == 0 {
+                    // SAFETY: no arguments
+                    return match unsafe { GetLastError() } {
+                        REQ_TIMED_OUT => Ok(None),
+                        error => Err(io::Error::from_raw_os_error(error as i32)),
+                    };
+                }
+                // SAFETY: the buffer starts with the reply
+                let reply = unsafe { &*buffer.as_ptr().cast::<ICMPV6_ECHO_REPLY_LH>() };
+                let words = reply.Address.sin6_addr;
+                let mut octets = [0u8; 16];
+                for (pair, word) in octets.chunks_mut(2).zip(words) {
+                    pair.copy_from_slice(&word.to_ne_bytes());
+                }
+                (
+                    reply.Status,
+                    IpAddr::V6(Ipv6Addr::from(octets)),
+                    None,
+                    size + 8,
+                )
+            }
+        };
+        let v6 = self.target.is_ipv6();
+        let kind = match status {
+            SUCCESS => ReplyKind::Echo,
+            TTL_EXPIRED_TRANSIT => ReplyKind::TimeExceeded,
+            DEST_NET_UNREACHABLE => ReplyKind::Unreachable(Unreachable::Network),
+            DEST_HOST_UNREACHABLE => ReplyKind::Unreachable(Unreachable::Host),
+            // ICMPv6 has no protocol unreachable and gives the code to
+            // prohibited destinations instead
+            DEST_PROT_UNREACHABLE if v6 => ReplyKind::Unreachable(Unreachable::Prohibited),
+            DEST_PROT_UNREACHABLE => ReplyKind::Unreachable(Unreachable::Protocol),
+            DEST_PORT_UNREACHABLE => ReplyKind::Unreachable(Unreachable::Port),
+            REQ_TIMED_OUT => return Ok(None),
+            status => return Err(io::Error::from_raw_os_error(status as i32)),
+        };
+        Ok(Some(Reply {
+            kind,
+            from,
+            size: reply_size,
+            ttl: reply_ttl,
+            rtt: started.elapsed(),
+        }))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Prober {
+    fn drop(&mut self) {
+        use windows_sys::Win32::NetworkManagement::IpHelper::IcmpCloseHandle;
+
+        // SAFETY: the handle came from IcmpCreateFile or Icmp6CreateFile and
+        // is closed once
+        unsafe {
+            IcmpCloseHandle(self.handle);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+impl Prober {
+    pub fn open(_target: IpAddr, _kind: ProbeKind) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ICMP probes are not supported on this platform",
+        ))
+    }
+
+    pub fn probe(
+        &mut self,
+        _seq: u16,
+        _ttl: Option<u8>,
+        _size: usize,
+        _timeout: Duration,
+        _stop: &mut dyn FnMut() -> bool,
+    ) -> io::Result<Option<Reply>> {
+        Ok(None)
+    }
+}
+
+/// The ICMP types of an echo request and of its reply
+#[cfg(unix)]
+fn echo_types(v6: bool) -> (u8, u8) {
+    if v6 {
+        (ECHO_REQUEST_V6, ECHO_REPLY_V6)
+    } else {
+        (ECHO_REQUEST_V4, ECHO_REPLY_V4)
+    }
+}
+
+/// An echo request with `size` bytes of data. The ICMPv6 checksum covers
+/// the addresses as well and is left to the kernel.
+#[cfg(unix)]
+fn echo_request(v6: bool, ident: u16, seq: u16, size: usize) -> Vec<u8> {
+    let mut packet = vec![echo_types(v6).0, 0, 0, 0];
+    packet.extend_from_slice(&ident.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend((0..size).map(|i| i as u8));
+    if !v6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// The data of a UDP probe, which starts with its sequence number so that a
+/// report quoting it can be told apart
+#[cfg(unix)]
+fn udp_payload(seq: u16, size: usize) -> Vec<u8> {
+    let mut payload = seq.to_be_bytes().to_vec();
+    payload.extend((2..size.max(2)).map(|i| i as u8));
+    payload
+}
+
+/// The Internet checksum of RFC 1071
+#[cfg(unix)]
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| {
+            u32::from(u16::from_be_bytes([
+                pair[0],
+                pair.get(1).copied().unwrap_or(0),
+            ]))
+        })
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Whether `icmp` starts an echo message of type `echo_type` for probe
+/// `seq`, with identifier `ident` unless that is left to the kernel
+#[cfg(unix)]
+fn is_echo(icmp: &[u8], echo_type: u8, ident: Option<u16>, seq: u16) -> bool {
+    if icmp.len() < 8 || icmp[0] != echo_type || icmp[6..8] != seq.to_be_bytes() {
+        return false;
+    }
+    match ident {
+        Some(ident) => icmp[4..6] == ident.to_be_bytes(),
+        None => true,
+    }
+}
+
+/// The kind of an ICMP error message, or None for any other message
+#[cfg(unix)]
+fn error_kind(v6: bool, icmp_type: u8, code: u8) -> Option<ReplyKind> {
+    match (v6, icmp_type) {
+        (false, TIME_EXCEEDED_V4) | (true, TIME_EXCEEDED_V6) => Some(ReplyKind::TimeExceeded),
+        (false, UNREACHABLE_V4) => Some(ReplyKind::Unreachable(Unreachable::from_v4(code))),
+        (true, UNREACHABLE_V6) => Some(ReplyKind::Unreachable(Unreachable::from_v6(code))),
+        _ => None,
+    }
+}
+
+/// Make sense of a message read from an ICMP socket as the answer to probe
+/// `seq`: its kind, the length of the ICMP message and the time to live of
+/// the IPv4 header that raw sockets and some datagram sockets leave in front
+#[cfg(unix)]
+fn parse_reply(
+    message: &[u8],
+    v6: bool,
+    ident: Option<u16>,
+    seq: u16,
+) -> Option<(ReplyKind, usize, Option<u8>)> {
+    let (icmp, ttl) = match message.first() {
+        Some(first) if !v6 && first >> 4 == 4 => {
+            let header = usize::from(first & 0xf) * 4;
+            (message.get(header..)?, message.get(8).copied())
+        }
+        _ => (message, None),
+    };
+    let (request, reply) = echo_types(v6);
+    if icmp.first() == Some(&reply) {
+        return is_echo(icmp, reply, ident, seq).then_some((ReplyKind::Echo, icmp.len(), ttl));
+    }
+    let kind = error_kind(v6, *icmp.first()?, *icmp.get(1)?)?;
+    // An error quotes the IP header of the probe and the start of its
+    // ICMP message
+    let quoted = icmp.get(8..)?;
+    let original = if v6 {
+        quoted.get(40..)?
+    } else {
+        quoted.get(usize::from(quoted.first()? & 0xf) * 4..)?
+    };
+    is_echo(original, request, ident, seq).then_some((kind, icmp.len(), ttl))
+}
+
+/// Wait up to `wait` for the socket to become readable or have an error
+/// queued, returning the events that came
+#[cfg(unix)]
+fn poll(fd: std::os::unix::io::RawFd, wait: Duration) -> io::Result<libc::c_short> {
+    let mut entry = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = libc::c_int::try_from(wait.as_micros().div_ceil(1000)).unwrap_or(libc::c_int::MAX);
+    // SAFETY: one live pollfd is passed
+    if unsafe { libc::poll(&mut entry, 1, millis) } < 0 {
+        let error = io::Error::last_os_error();
+        return match error.kind() {
+            io::ErrorKind::Interrupted => Ok(0),
+            _ => Err(error),
+        };
+    }
+    Ok(entry.revents)
+}
+
+/// What `recvmsg` read into the buffer
+#[cfg(unix)]
+struct Received {
+    len: usize,
+    from: Option<IpAddr>,
+    /// The time to live or hop limit from the control messages
+    ttl: Option<u8>,
+    #[cfg(target_os = "linux")]
+    error: Option<QueuedError>,
+}
+
+/// A report read from a socket's error queue
+#[cfg(target_os = "linux")]
+struct QueuedError {
+    origin: u8,
+    icmp_type: u8,
+    code: u8,
+    /// Whoever sent the report
+    offender: Option<IpAddr>,
+}
+
+/// Read a message without waiting, from the error queue when `flags` has
+/// `MSG_ERRQUEUE`
+#[cfg(unix)]
+fn receive(
+    fd: std::os::unix::io::RawFd,
+    buffer: &mut [u8],
+    flags: libc::c_int,
+) -> io::Result<Received> {
+    use std::mem;
+
+    // SAFETY: socket addresses and message headers are plain data, valid
+    // when all zero
+    let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut header: libc::msghdr = unsafe { mem::zeroed() };
+    // u64s keep the control messages aligned
+    let mut control = [0u64; 64];
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: buffer.len(),
+    };
+    header.msg_name = (&mut name as *mut libc::sockaddr_storage).cast();
+    header.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    header.msg_controllen = mem::size_of_val(&control) as _;
+    // SAFETY: the header points at live buffers of the sizes it gives
+    let read = unsafe { libc::recvmsg(fd, &mut header, flags | libc::MSG_DONTWAIT) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(unused_mut)]
+    let mut received = Received {
+        len: read as usize,
+        // SAFETY: the kernel filled in an address of the family it names,
+        // or left the family zero
+        from: unsafe { socket_ip((&name as *const libc::sockaddr_storage).cast()) },
+        ttl: None,
+        #[cfg(target_os = "linux")]
+        error: None,
+    };
+    #[cfg(target_os = "linux")]
+    // SAFETY: recvmsg filled in the control messages the header describes
+    unsafe {
+        read_control(&header, &mut received);
+    }
+    Ok(received)
+}
+
+/// Take the time to live and any queued error from the control messages
+#[cfg(target_os = "linux")]
+unsafe fn read_control(header: &libc::msghdr, received: &mut Received) {
+    use std::{mem, ptr};
+
+    let mut message = libc::CMSG_FIRSTHDR(header);
+    while let Some(control) = message.as_ref() {
+        let data = libc::CMSG_DATA(message);
+        match (control.cmsg_level, control.cmsg_type) {
+            (libc::SOL_IP, libc::IP_TTL) | (libc::SOL_IPV6, libc::IPV6_HOPLIMIT) => {
+                let ttl = ptr::read_unaligned(data.cast::<libc::c_int>());
+                received.ttl = u8::try_from(ttl).ok();
+            }
+            (libc::SOL_IP, libc::IP_RECVERR) | (libc::SOL_IPV6, libc::IPV6_RECVERR) => {
+                let error = ptr::read_unaligned(data.cast::<libc::sock_extended_err>());
+                received.error = Some(QueuedError {
+                    origin: error.ee_origin,
+                    icmp_type: error.ee_type,
+                    code: error.ee_code,
+                    // The sender's address follows the error
+                    offender: socket_ip(data.add(mem::size_of::<libc::sock_extended_err>())),
+                });
+            }
+            _ => {}
+        }
+        message = libc::CMSG_NXTHDR(header, message);
+    }
+}
+
+/// The address in a socket address the kernel filled in, read without
+/// assuming it is aligned
+#[cfg(unix)]
+unsafe fn socket_ip(address: *const u8) -> Option<IpAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::ptr;
+
+    let family = ptr::read_unaligned(address.cast::<libc::sockaddr>()).sa_family;
+    match libc::c_int::from(family) {
+        libc::AF_INET => {
+            let address = ptr::read_unaligned(address.cast::<libc::sockaddr_in>());
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                address.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let address = ptr::read_unaligned(address.cast::<libc::sockaddr_in6>());
+            Some(IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
+
+/// Turn on an integer socket option
+#[cfg(target_os = "linux")]
+fn enable(socket: &socket2::Socket, level: libc::c_int, name: libc::c_int) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let on: libc::c_int = 1;
+    // SAFETY: the value is a live c_int of the size given
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            (&on as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_codes_of_both_families() {
+        assert_eq!(Unreachable::from_v4(1), Unreachable::Host);
+        assert_eq!(Unreachable::from_v4(13), Unreachable::Prohibited);
+        assert_eq!(Unreachable::from_v6(4), Unreachable::Port);
+        assert_eq!(Unreachable::from_v6(3), Unreachable::Host);
+        assert_eq!(
+            ReplyKind::Unreachable(Unreachable::from_v4(3)).to_string(),
+            "Destination Port Unreachable"
+        );
+        assert_eq!(ReplyKind::TimeExceeded.to_string(), "Time to live exceeded");
+    }
+
+    #[test]
+    fn resolves_addresses_of_the_family_asked_for() {
+        assert_eq!(
+            resolve("127.0.0.1", Family::Any).unwrap(),
+            "127.0.0.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            resolve("::1", Family::V6).unwrap(),
+            "::1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            resolve("::1", Family::V4).unwrap_err(),
+            "::1: Address family for hostname not supported"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn builds_echo_requests_with_checksums() {
+        let request = echo_request(false, 0x1234, 7, 4);
+        assert_eq!(request[..2], [ECHO_REQUEST_V4, 0]);
+        assert_eq!(request[4..], [0x12, 0x34, 0, 7, 0, 1, 2, 3]);
+        // A message with its checksum in place sums to zero
+        assert_eq!(checksum(&request), 0);
+        assert_eq!(checksum(&[0x45, 0x00, 0x00]), !0x4500);
+
+        let request = echo_request(true, 1, 2, 0);
+        assert_eq!(request, [ECHO_REQUEST_V6, 0, 0, 0, 0, 1, 0, 2]);
+        assert_eq!(udp_payload(0x0102, 4), [1, 2, 2, 3]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parses_replies_and_the_probes_errors_quote() {
+        // An echo reply behind a 20 byte IPv4 header with a TTL of 57
+        let mut header = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 57, 1];
+        header.resize(20, 0);
+        let mut reply = echo_request(false, 9, 3, 4);
+        reply[0] = ECHO_REPLY_V4;
+        let message = [header.clone(), reply.clone()].concat();
+        assert_eq!(
+            parse_reply(&message, false, Some(9), 3),
+            Some((ReplyKind::Echo, 12, Some(57)))
+        );
+        assert_eq!(
+            parse_reply(&reply, false, None, 3),
+            Some((ReplyKind::Echo, 12, None))
+        );
+        assert_eq!(parse_reply(&message, false, Some(8), 3), None);
+        assert_eq!(parse_reply(&message, false, Some(9), 4), None);
+
+        // A time exceeded report quoting the request
+        let mut report = vec![TIME_EXCEEDED_V4, 0, 0, 0, 0, 0, 0, 0];
+        report.extend_from_slice(&header);
+        report.extend_from_slice(&echo_request(false, 9, 3, 0));
+        assert_eq!(
+            parse_reply(&report, false, Some(9), 3),
+            Some((ReplyKind::TimeExceeded, 36, None))
+        );
+        report[0] = UNREACHABLE_V4;
+        report[1] = 1;
+        assert_eq!(
+            parse_reply(&report, false, Some(9), 3).unwrap().0,
+            ReplyKind::Unreachable(Unreachable::Host)
+        );
+        assert_eq!(parse_reply(&report, false, Some(9), 2), None);
+
+        // ICMPv6 comes without the IP header, and quotes a 40 byte one
+        let mut report = vec![TIME_EXCEEDED_V6, 0, 0, 0, 0, 0, 0, 0];
+        report.extend_from_slice(&[0x60; 40]);
+        report.extend_from_slice(&echo_request(true, 9, 5, 0));
+        assert_eq!(
+            parse_reply(&report, true, None, 5),
+            Some((ReplyKind::TimeExceeded, 56, None))
+        );
+        assert_eq!(parse_reply(&[ECHO_REQUEST_V6, 0], true, None, 5), None);
+    }
+}
@@ -48,6 +48,17 @@ pub fn format_float_locale(value: f64, precision: usize, langid: &str) -> String
     s
 }
 
+/// Decimal point and thousands separator of a POSIX locale name such as
+/// `de_DE.UTF-8`; the C and POSIX locales group nothing
+pub fn numeric_symbols(locale: &str) -> (&'static str, &'static str) {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return (".", "");
+    }
+    let loc = resolve_num_locale(&lang_code(name));
+    (loc.decimal(), loc.separator())
+}
+
 pub fn format_date_locale(ts: i64, langid: &str) -> String {
     let dt: DateTime<Local> = Local
         .timestamp_opt(ts, 0)
@@ -111,6 +122,13 @@ mod tests {
         assert!(de.contains(","));
     }
 
+    #[test]
+    fn test_numeric_symbols() {
+        assert_eq!(numeric_symbols("C"), (".", ""));
+        assert_eq!(numeric_symbols("en_US.UTF-8"), (".", ","));
+        assert_eq!(numeric_symbols("de_DE.UTF-8"), (",", "."));
+    }
+
     #[test]
     fn test_format_date_locale() {
         let ts = Local
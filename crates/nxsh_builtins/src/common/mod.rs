@@ -1,26 +1,37 @@
 pub mod crash_diagnosis;
+pub mod decompress;
+pub mod dns;
+pub mod http;
 #[cfg(feature = "i18n")]
 pub mod i18n; // full implementation
 #[cfg(not(feature = "i18n"))]
 pub mod i18n; // stub (same file exports stub when feature off)
+pub mod icmp;
 pub mod locale_format;
 pub mod logging;
 #[cfg(feature = "async-runtime")]
 pub mod metrics;
 #[cfg(not(feature = "async-runtime"))]
 pub mod metrics; // stub when async runtime disabled
+pub mod operands;
 pub mod process_utils;
 pub mod resource_monitor;
 pub mod sed_utils;
+#[cfg(feature = "remote")]
+pub mod ssh;
 #[cfg(feature = "async-runtime")]
 pub mod update_system;
 #[cfg(not(feature = "async-runtime"))]
 pub mod update_system; // stub
+pub mod wait;
 
+use nxsh_core::ExecutionResult;
+use nxsh_hal::HalError;
 use std::collections::HashMap;
 use std::env;
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Result type for built-in commands
 pub type BuiltinResult<T> = Result<T, BuiltinError>;
@@ -77,6 +88,33 @@ impl From<&str> for BuiltinError {
     }
 }
 
+/// An I/O error the way the builtins word it, without Rust's `(os error N)`
+pub fn io_message(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::NotFound => "No such file or directory".to_string(),
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        _ => {
+            let text = e.to_string();
+            match text.find(" (os error") {
+                Some(at) => text[..at].to_string(),
+                None => text,
+            }
+        }
+    }
+}
+
+/// What went wrong in a HAL call, worded like [`io_message`] words an I/O
+/// error and without the name of the system call
+pub fn hal_message(error: &HalError) -> String {
+    match error {
+        HalError::Io(io) => io_message(&io::Error::new(io.kind, io.message.clone())),
+        HalError::Process(process) => process.message.clone(),
+        HalError::Invalid(message) => message.clone(),
+        HalError::Unsupported(_) => "Operation not supported".to_string(),
+        other => other.to_string(),
+    }
+}
+
 /// Context for built-in command execution
 #[derive(Debug, Clone)]
 pub struct BuiltinContext {
@@ -139,6 +177,77 @@ impl BuiltinContext {
     }
 }
 
+/// The outcome of a usage error: status 2 with `message` on standard error
+pub fn usage_error(message: &str) -> ExecutionResult {
+    ExecutionResult::failure(2).with_error(format!("{message}\n").into_bytes())
+}
+
+/// Single-quote `s` so the command printed with it can be pasted back into
+/// the shell
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// A number of seconds, fractions allowed, capped where a Duration would
+/// overflow; `None` unless `text` is a finite number no less than zero
+pub fn seconds(text: &str) -> Option<Duration> {
+    match text.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => {
+            Some(Duration::from_secs_f64(secs.min(f64::from(u32::MAX))))
+        }
+        _ => None,
+    }
+}
+
+/// An interval the way `sleep` takes it: a number of seconds, fractions
+/// allowed, with an optional `s`, `m`, `h` or `d` suffix
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    if s.is_empty() {
+        return Err("invalid time interval".to_string());
+    }
+
+    // Handle suffixes
+    let (number_str, suffix) = if let Some(stripped) = s.strip_suffix('s') {
+        (stripped, "s")
+    } else if let Some(stripped) = s.strip_suffix('m') {
+        (stripped, "m")
+    } else if let Some(stripped) = s.strip_suffix('h') {
+        (stripped, "h")
+    } else if let Some(stripped) = s.strip_suffix('d') {
+        (stripped, "d")
+    } else {
+        (s, "s") // Default to seconds
+    };
+
+    // Parse the number part
+    let number: f64 = number_str
+        .parse()
+        .map_err(|_| format!("invalid time interval '{s}'"))?;
+
+    if number < 0.0 {
+        return Err("invalid time interval".to_string());
+    }
+
+    // Convert to seconds based on suffix
+    let seconds = match suffix {
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        _ => return Err(format!("invalid time interval '{s}'")),
+    };
+
+    // Convert to Duration
+    let duration = Duration::from_secs_f64(seconds);
+
+    // Check for reasonable limits (avoid overflow)
+    if seconds > u64::MAX as f64 {
+        return Err("time interval too large".to_string());
+    }
+
+    Ok(duration)
+}
+
 /// Table formatter for structured output
 #[derive(Debug, Clone)]
 pub struct TableFormatter {
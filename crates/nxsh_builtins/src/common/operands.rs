@@ -0,0 +1,71 @@
+//! FILE operands of text builtins
+//!
+//! `Inputs` reads a named file relative to the shell's working directory, or
+//! standard input for `-`. Standard input can be named more than once, as in
+//! `paste - -`, but is only read once: later reads find it at its end.
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Where a builtin's FILE operands are read from
+pub struct Inputs<'a> {
+    cwd: &'a Path,
+    stdin: &'a mut dyn Read,
+    stdin_read: bool,
+}
+
+impl<'a> Inputs<'a> {
+    pub fn new(cwd: &'a Path, stdin: &'a mut dyn Read) -> Self {
+        Inputs {
+            cwd,
+            stdin,
+            stdin_read: false,
+        }
+    }
+
+    /// The path of the file NAME
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.cwd.join(name)
+    }
+
+    /// All of the file NAME, or of standard input for `-`
+    pub fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        if name != "-" {
+            return std::fs::read(self.path(name));
+        }
+        if !self.stdin_read {
+            self.stdin_read = true;
+            self.stdin.read_to_end(&mut data)?;
+        }
+        Ok(data)
+    }
+}
+
+/// DATA split into lines, each without its newline; a final line missing
+/// one still counts
+pub fn lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = data.split(|&byte| byte == b'\n').collect();
+    if data.is_empty() || data.ends_with(b"\n") {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_standard_input_once() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("f"), "file").unwrap();
+        let mut stdin = &b"in"[..];
+        let mut inputs = Inputs::new(dir.path(), &mut stdin);
+        assert_eq!(inputs.read("-").unwrap(), b"in");
+        assert_eq!(inputs.read("-").unwrap(), b"");
+        assert_eq!(inputs.read("f").unwrap(), b"file");
+        assert_eq!(lines(b"a\n\nb"), vec![&b"a"[..], b"", b"b"]);
+        assert!(lines(b"").is_empty());
+    }
+}
@@ -0,0 +1,553 @@
+//! SSH sessions for the `ssh` and `scp` builtins
+//!
+//! [`Session::connect`] reaches `[USER@]HOST` over SSH 2, checks the host's
+//! key against `~/.ssh/known_hosts` and logs in the way OpenSSH does: with
+//! the keys the agent behind `SSH_AUTH_SOCK` holds, then with the identity
+//! files, asking for their passphrases, and last with a password. Prompts go
+//! to the terminal and are never made in batch mode or without one.
+//!
+//! The session runs on a runtime of its own, so that the builtins, which are
+//! not async, can drive it with [`Session::block_on`].
+
+use super::io_message;
+use nxsh_ui::readline::{read_raw, RawReadOptions, RawReadOutcome};
+use russh::client::{self, Handle, Msg};
+use russh::{Channel, Disconnect};
+use russh_keys::key::{KeyPair, PublicKey};
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The port SSH servers listen on
+pub const DEFAULT_PORT: u16 = 22;
+
+/// The most passwords asked for before giving up, as OpenSSH does
+const PASSWORD_TRIES: usize = 3;
+
+/// What to do with a host key that `known_hosts` does not list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyPolicy {
+    /// Refuse it (`StrictHostKeyChecking=yes`)
+    Strict,
+    /// Add it without asking (`accept-new`)
+    AcceptNew,
+    /// Ask whether to add it (`ask`, the default)
+    Ask,
+    /// Take any key, even one that differs from the listed one (`no`)
+    Off,
+}
+
+/// How to reach and log in to hosts, from the command line and `-o`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    /// Identity files from `-i`, tried before the usual ones
+    pub identities: Vec<PathBuf>,
+    pub known_hosts: Option<PathBuf>,
+    pub policy: HostKeyPolicy,
+    /// Never prompt (`BatchMode=yes`)
+    pub batch: bool,
+    pub connect_timeout: Option<Duration>,
+    /// Leave out warnings
+    pub quiet: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            port: None,
+            user: None,
+            identities: Vec::new(),
+            known_hosts: None,
+            policy: HostKeyPolicy::Ask,
+            batch: false,
+            connect_timeout: None,
+            quiet: false,
+        }
+    }
+}
+
+impl Options {
+    /// Take one `-o` option, written `Key=Value` or `Key Value`
+    pub fn set(&mut self, option: &str) -> Result<(), String> {
+        let (key, value) = option
+            .split_once('=')
+            .or_else(|| option.trim().split_once(char::is_whitespace))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| format!("missing argument for option '{option}'"))?;
+        let yes_no = |value: &str| match value.to_ascii_lowercase().as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(format!("unsupported option \"{key}\" value \"{value}\"")),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "stricthostkeychecking" => {
+                self.policy = match value.to_ascii_lowercase().as_str() {
+                    "yes" => HostKeyPolicy::Strict,
+                    "accept-new" => HostKeyPolicy::AcceptNew,
+                    "ask" => HostKeyPolicy::Ask,
+                    "no" | "off" => HostKeyPolicy::Off,
+                    _ => return Err(format!("unsupported option \"{key}\" value \"{value}\"")),
+                }
+            }
+            "userknownhostsfile" => self.known_hosts = Some(expand_home(value)),
+            "identityfile" => self.identities.push(expand_home(value)),
+            "batchmode" => self.batch = yes_no(value)?,
+            "connecttimeout" => {
+                let seconds: u64 = value
+                    .parse()
+                    .map_err(|_| format!("invalid time value \"{value}\""))?;
+                self.connect_timeout = Some(Duration::from_secs(seconds));
+            }
+            "port" => self.port = Some(parse_port(value)?),
+            "user" => self.user = Some(value.to_string()),
+            "loglevel" => self.quiet = value.eq_ignore_ascii_case("quiet"),
+            _ => return Err(format!("unsupported option \"{key}\"")),
+        }
+        Ok(())
+    }
+
+    /// The host, user and port for a `[USER@]HOST` operand
+    pub fn target(&self, destination: &str) -> Result<Target, String> {
+        let (user, host) = match destination.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (self.user.clone(), destination),
+        };
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() {
+            return Err(format!("invalid destination '{destination}'"));
+        }
+        Ok(Target {
+            user: user.unwrap_or_else(whoami::username),
+            host: host.to_string(),
+            port: self.port.unwrap_or(DEFAULT_PORT),
+        })
+    }
+}
+
+/// A port number from 1 to 65535
+pub fn parse_port(text: &str) -> Result<u16, String> {
+    match text.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err(format!("Bad port '{text}'")),
+    }
+}
+
+/// Where to log in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+}
+
+/// Quote `text` for a POSIX shell on the other host
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// An open, logged in SSH connection
+pub struct Session {
+    runtime: tokio::runtime::Runtime,
+    handle: Handle<HostCheck>,
+}
+
+impl Session {
+    /// Connect to `target` and log in, writing warnings to `errors`; the
+    /// error is the message OpenSSH would print, without the program name
+    pub fn connect(
+        target: &Target,
+        options: &Options,
+        errors: &mut dyn Write,
+    ) -> Result<Session, String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| io_message(&e))?;
+        let prompt = !options.batch && io::stdin().is_terminal();
+        let check = HostCheck {
+            host: target.host.clone(),
+            port: target.port,
+            known_hosts: options
+                .known_hosts
+                .clone()
+                .or_else(|| ssh_dir().map(|dir| dir.join("known_hosts"))),
+            policy: options.policy,
+            prompt,
+            notes: Arc::default(),
+            refused: Arc::default(),
+        };
+        let notes = check.notes.clone();
+        let refused = check.refused.clone();
+        let handle = runtime.block_on(async {
+            let config = Arc::new(client::Config::default());
+            let address = (target.host.as_str(), target.port);
+            let connecting = client::connect(config, address, check);
+            match options.connect_timeout {
+                Some(limit) => tokio::time::timeout(limit, connecting)
+                    .await
+                    .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut).into())),
+                None => connecting.await,
+            }
+        });
+        for note in notes.lock().unwrap().drain(..) {
+            if !options.quiet || note.starts_with('@') {
+                let _ = writeln!(errors, "{note}");
+            }
+        }
+        let mut handle = match handle {
+            Ok(handle) => handle,
+            Err(_) if *refused.lock().unwrap() => {
+                return Err("Host key verification failed.".to_string())
+            }
+            Err(russh::Error::IO(e)) => {
+                return Err(format!(
+                    "connect to host {} port {}: {}",
+                    target.host,
+                    target.port,
+                    io_message(&e)
+                ));
+            }
+            Err(e) => return Err(format!("{}: {e}", target.host)),
+        };
+        let logged_in = runtime.block_on(log_in(&mut handle, target, options, prompt, errors));
+        match logged_in {
+            Ok(true) => Ok(Session { runtime, handle }),
+            Ok(false) => Err(format!(
+                "{}@{}: Permission denied (publickey,password).",
+                target.user, target.host
+            )),
+            Err(e) => Err(format!("{}: {e}", target.host)),
+        }
+    }
+
+    /// Run `future` on the session's runtime
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Open a channel for a shell or a command
+    pub async fn channel(&self) -> Result<Channel<Msg>, String> {
+        self.handle
+            .channel_open_session()
+            .await
+            .map_err(|e| format!("channel open failed: {e}"))
+    }
+
+    /// Say goodbye to the host
+    pub fn close(self) {
+        let _ = self
+            .runtime
+            .block_on(self.handle.disconnect(Disconnect::ByApplication, "", "en"));
+    }
+}
+
+/// `~/.ssh`
+fn ssh_dir() -> Option<PathBuf> {
+    dirs_next::home_dir().map(|home| home.join(".ssh"))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/").zip(dirs_next::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Ask on the terminal, without echo for secrets
+fn ask(prompt: &str, echo: bool) -> Option<String> {
+    let options = RawReadOptions {
+        echo,
+        ..RawReadOptions::default()
+    };
+    match read_raw(prompt, &options) {
+        Ok(RawReadOutcome::Complete(answer)) => {
+            if !echo {
+                eprintln!();
+            }
+            Some(answer)
+        }
+        _ => None,
+    }
+}
+
+/// `ED25519` for `ssh-ed25519` and so on, as key types are shown
+fn key_kind(key: &PublicKey) -> &'static str {
+    match key.name() {
+        "ssh-ed25519" => "ED25519",
+        name if name.starts_with("ecdsa") => "ECDSA",
+        _ => "RSA",
+    }
+}
+
+/// Checks the host's key against `known_hosts`, keeping what it has to say
+/// for [`Session::connect`] to print
+struct HostCheck {
+    host: String,
+    port: u16,
+    known_hosts: Option<PathBuf>,
+    policy: HostKeyPolicy,
+    prompt: bool,
+    notes: Arc<Mutex<Vec<String>>>,
+    refused: Arc<Mutex<bool>>,
+}
+
+impl HostCheck {
+    fn note(&self, text: String) {
+        self.notes.lock().unwrap().push(text);
+    }
+
+    /// The name `known_hosts` lists the host under
+    fn shown(&self) -> String {
+        if self.port == DEFAULT_PORT {
+            self.host.clone()
+        } else {
+            format!("[{}]:{}", self.host, self.port)
+        }
+    }
+
+    fn learn(&self, key: &PublicKey, path: &Path) {
+        match russh_keys::learn_known_hosts_path(&self.host, self.port, key, path) {
+            Ok(()) => self.note(format!(
+                "Warning: Permanently added '{}' ({}) to the list of known hosts.",
+                self.shown(),
+                key_kind(key)
+            )),
+            Err(e) => self.note(format!(
+                "Failed to add the host to the list of known hosts ({}): {e}",
+                path.display()
+            )),
+        }
+    }
+
+    fn verify(&self, key: &PublicKey) -> bool {
+        let Some(path) = &self.known_hosts else {
+            return self.policy == HostKeyPolicy::Off;
+        };
+        let fingerprint = format!("SHA256:{}", key.fingerprint());
+        match russh_keys::check_known_hosts_path(&self.host, self.port, key, path) {
+            Ok(true) => true,
+            Ok(false) => match self.policy {
+                HostKeyPolicy::Off | HostKeyPolicy::AcceptNew => {
+                    self.learn(key, path);
+                    true
+                }
+                HostKeyPolicy::Strict => {
+                    self.note(format!(
+                        "No {} host key is known for {} and you have requested strict checking.",
+                        key_kind(key),
+                        self.shown()
+                    ));
+                    false
+                }
+                HostKeyPolicy::Ask => {
+                    if !self.prompt {
+                        return false;
+                    }
+                    eprintln!(
+                        "The authenticity of host '{}' can't be established.\n\
+                         {} key fingerprint is {fingerprint}.",
+                        self.shown(),
+                        key_kind(key)
+                    );
+                    let mut question = "Are you sure you want to continue connecting (yes/no)? ";
+                    let agreed = loop {
+                        match ask(question, true).as_deref().map(str::trim) {
+                            Some("yes") => break true,
+                            Some("no") | None => break false,
+                            Some(_) => question = "Please type 'yes' or 'no': ",
+                        }
+                    };
+                    if agreed {
+                        self.learn(key, path);
+                    }
+                    agreed
+                }
+            },
+            Err(russh_keys::Error::KeyChanged { line }) => {
+                self.note(format!(
+                    "@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+                     @    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\n\
+                     @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+                     The {} key sent by the remote host is\n{fingerprint}.\n\
+                     Offending key in {}:{line}",
+                    key_kind(key),
+                    path.display()
+                ));
+                self.policy == HostKeyPolicy::Off
+            }
+            Err(e) => {
+                self.note(format!("{}: {e}", path.display()));
+                self.policy == HostKeyPolicy::Off
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl client::Handler for HostCheck {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, key: &PublicKey) -> Result<bool, Self::Error> {
+        let accepted = self.verify(key);
+        *self.refused.lock().unwrap() = !accepted;
+        Ok(accepted)
+    }
+}
+
+/// Try the agent's keys, then the identity files, then passwords, returning
+/// whether the host let the user in
+async fn log_in(
+    handle: &mut Handle<HostCheck>,
+    target: &Target,
+    options: &Options,
+    prompt: bool,
+    errors: &mut dyn Write,
+) -> Result<bool, russh::Error> {
+    #[cfg(unix)]
+    if let Ok(mut agent) = russh_keys::agent::client::AgentClient::connect_env().await {
+        for key in agent.request_identities().await.unwrap_or_default() {
+            let (returned, accepted) = handle
+                .authenticate_future(target.user.as_str(), key, agent)
+                .await;
+            agent = returned;
+            if accepted.unwrap_or(false) {
+                return Ok(true);
+            }
+        }
+    }
+
+    let usual = ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .filter_map(|name| Some(ssh_dir()?.join(name)))
+        .filter(|path| path.exists());
+    let explicit = options.identities.len();
+    for (at, path) in options.identities.iter().cloned().chain(usual).enumerate() {
+        let Some(key) = identity(&path, at < explicit, prompt, errors) else {
+            continue;
+        };
+        if handle
+            .authenticate_publickey(target.user.as_str(), Arc::new(key))
+            .await?
+        {
+            return Ok(true);
+        }
+    }
+
+    if !prompt {
+        return Ok(false);
+    }
+    let question = format!("{}@{}'s password: ", target.user, target.host);
+    for attempt in 0..PASSWORD_TRIES {
+        if attempt > 0 {
+            eprintln!("Permission denied, please try again.");
+        }
+        let Some(password) = ask(&question, false) else {
+            return Ok(false);
+        };
+        if handle
+            .authenticate_password(target.user.as_str(), password)
+            .await?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Load the key in `path`, asking for its passphrase when it has one;
+/// files named with `-i` are reported when they cannot be read
+fn identity(path: &Path, named: bool, prompt: bool, errors: &mut dyn Write) -> Option<KeyPair> {
+    match russh_keys::load_secret_key(path, None) {
+        Ok(key) => Some(key),
+        Err(russh_keys::Error::KeyIsEncrypted) if prompt => {
+            let question = format!("Enter passphrase for key '{}': ", path.display());
+            let passphrase = ask(&question, false)?;
+            match russh_keys::load_secret_key(path, Some(&passphrase)) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    let _ = writeln!(errors, "Load key \"{}\": {e}", path.display());
+                    None
+                }
+            }
+        }
+        Err(russh_keys::Error::IO(e)) if named => {
+            let _ = writeln!(
+                errors,
+                "Warning: Identity file {} not accessible: {}.",
+                path.display(),
+                io_message(&e)
+            );
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_dash_o_options() {
+        let mut options = Options::default();
+        options.set("StrictHostKeyChecking=accept-new").unwrap();
+        options.set("BatchMode yes").unwrap();
+        options.set("Port=2222").unwrap();
+        options.set("ConnectTimeout=5").unwrap();
+        options.set("UserKnownHostsFile=/tmp/hosts").unwrap();
+        assert_eq!(options.policy, HostKeyPolicy::AcceptNew);
+        assert!(options.batch);
+        assert_eq!(options.port, Some(2222));
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(options.known_hosts, Some(PathBuf::from("/tmp/hosts")));
+
+        assert_eq!(
+            options.set("BatchMode=maybe").unwrap_err(),
+            "unsupported option \"BatchMode\" value \"maybe\""
+        );
+        assert_eq!(
+            options.set("Compression=yes").unwrap_err(),
+            "unsupported option \"Compression\""
+        );
+        assert_eq!(options.set("Port=0").unwrap_err(), "Bad port '0'");
+        assert_eq!(
+            options.set("Port").unwrap_err(),
+            "missing argument for option 'Port'"
+        );
+    }
+
+    #[test]
+    fn splits_destinations() {
+        let mut options = Options {
+            port: Some(2200),
+            user: Some("admin".to_string()),
+            ..Options::default()
+        };
+        let target = options.target("alice@example.com").unwrap();
+        assert_eq!(
+            target,
+            Target {
+                user: "alice".to_string(),
+                host: "example.com".to_string(),
+                port: 2200
+            }
+        );
+        assert_eq!(options.target("[::1]").unwrap().host, "::1");
+        assert_eq!(options.target("host").unwrap().user, "admin");
+        options.user = None;
+        assert_eq!(options.target("host").unwrap().user, whoami::username());
+        assert!(options.target("user@").is_err());
+    }
+
+    #[test]
+    fn quotes_for_the_remote_shell() {
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}
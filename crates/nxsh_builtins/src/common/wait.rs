@@ -0,0 +1,43 @@
+//! Waiting between frames without a terminal
+//!
+//! `top`, `watch` and `ping` sleep between updates. With no terminal to read
+//! keys from, [`sleep_unless`] still wakes every [`TICK`] so the shell's
+//! time limit can stop the command part way through an interval.
+
+use std::time::{Duration, Instant};
+
+/// How often a wait without a terminal checks the shell's time limit
+pub const TICK: Duration = Duration::from_millis(50);
+
+/// Sleep for `timeout` unless `stopped` says to give up first, asking it
+/// every [`TICK`]; true when it did
+pub fn sleep_unless(timeout: Duration, stopped: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if stopped() {
+            return true;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        std::thread::sleep(left.min(TICK));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn stops_at_the_next_tick() {
+        assert!(!sleep_unless(Duration::ZERO, || false));
+        let asked = Cell::new(0);
+        let stopped = sleep_unless(Duration::from_secs(60), || {
+            asked.set(asked.get() + 1);
+            asked.get() == 2
+        });
+        assert!(stopped);
+    }
+}
@@ -0,0 +1,1339 @@
+//! Conversions between text and structured values
+//!
+//! Syntax:
+//!   from-json [FILE]                        # JSON text to a value
+//!   to-json [-c|--compact] [--indent N]     # a value to JSON text
+//!   from-yaml [FILE]                        # YAML text to a value
+//!   to-yaml                                 # a value to YAML text
+//!   from-toml [FILE]                        # TOML text to a record
+//!   to-toml                                 # a record to TOML text
+//!   from-csv [-s SEP] [--no-header] [FILE]  # CSV text to a table
+//!   to-csv [-s SEP] [--no-header]           # a table to CSV text
+//!
+//! They are structured pipeline stages (see [`crate::structured`]), so the
+//! text comes from the command before them or from FILE:
+//!
+//!   cat data.json | from-json | where size -gt 100 | to-csv
+//!   from-toml Cargo.toml | to-yaml
+//!
+//! JSON and CSV files are parsed as they are read rather than loaded whole.
+//! A JSON or YAML stream of several values (concatenated JSON, or `---`
+//! separated YAML documents) becomes a list, or a table when every value is
+//! an object.
+//!
+//! Object keys keep the order they were written in: the top level's becomes
+//! the table's column order, and nested objects' is kept in the data's
+//! metadata for the `to-` commands. Of a YAML or TOML file's comments, the
+//! ones it starts with are written back by `to-yaml` and `to-toml`; the rest
+//! are dropped, as a structured value has nowhere to keep them.
+
+use chrono::{DateTime, Utc};
+use nxsh_core::context::ShellContext;
+use nxsh_core::error::{ErrorKind, RuntimeErrorKind};
+use nxsh_core::{PipelineData, ShellError, ShellResult, StructuredBuiltin, StructuredValue};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+
+type Row = HashMap<String, StructuredValue>;
+
+/// Metadata key holding the key order of nested objects, as JSON
+const KEY_ORDER: &str = "key-order";
+
+/// Metadata key holding the comment lines a YAML or TOML document starts with
+const COMMENTS: &str = "comments";
+
+/// Where a conversion reads its text: FILE, or the previous stage's string
+fn text_source(
+    ctx: &ShellContext,
+    file: Option<&String>,
+    input: Option<PipelineData>,
+) -> ShellResult<Box<dyn BufRead>> {
+    if let Some(file) = file {
+        let handle =
+            std::fs::File::open(ctx.cwd.join(file)).map_err(|e| invalid(format!("{file}: {e}")))?;
+        return Ok(Box::new(BufReader::new(handle)));
+    }
+    match input.map(|data| data.value) {
+        Some(StructuredValue::String(text)) => Ok(Box::new(std::io::Cursor::new(text))),
+        Some(other) => Err(invalid(format!(
+            "expects text input, got {}",
+            other.type_name()
+        ))),
+        None => Err(invalid("expects text input or a FILE")),
+    }
+}
+
+/// `from-json [FILE]`
+pub struct FromJson;
+
+impl StructuredBuiltin for FromJson {
+    fn name(&self) -> &'static str {
+        "from-json"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if args.len() > 1 {
+            return Err(invalid("usage: from-json [FILE]"));
+        }
+        let reader = text_source(ctx, args.first(), input)?;
+        let mut documents = Vec::new();
+        for document in serde_json::Deserializer::from_reader(reader).into_iter::<Document>() {
+            documents.push(document.map_err(|e| invalid(format!("invalid JSON: {e}")))?);
+        }
+        Ok(document_data(Document::stream(documents)))
+    }
+}
+
+/// The JSON documents in `text` as pipeline data, the way `from-json` reads
+/// them, for commands that fetch JSON themselves
+pub(crate) fn json_data(text: &[u8]) -> ShellResult<PipelineData> {
+    let mut documents = Vec::new();
+    for document in serde_json::Deserializer::from_slice(text).into_iter::<Document>() {
+        documents.push(document.map_err(|e| invalid(format!("invalid JSON: {e}")))?);
+    }
+    Ok(document_data(Document::stream(documents)))
+}
+
+/// `to-json [-c|--compact] [--indent N]`
+pub struct ToJson;
+
+impl StructuredBuiltin for ToJson {
+    fn name(&self) -> &'static str {
+        "to-json"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let mut indent = Some(2);
+        let mut rest = args;
+        while let Some(flag) = rest.first() {
+            match flag.as_str() {
+                "-c" | "--compact" => indent = None,
+                "--indent" => {
+                    let width = rest
+                        .get(1)
+                        .and_then(|width| width.parse().ok())
+                        .ok_or_else(|| invalid("--indent: expects a number"))?;
+                    indent = Some(width);
+                    rest = &rest[1..];
+                }
+                other => return Err(invalid(format!("{other}: invalid option"))),
+            }
+            rest = &rest[1..];
+        }
+        let data = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let order = KeyOrder::from_data(&data);
+        let mut out = String::new();
+        JsonWriter {
+            indent,
+            order: &order,
+            out: &mut out,
+        }
+        .write(&data.value, "", 0);
+        Ok(PipelineData::new(StructuredValue::String(out)))
+    }
+}
+
+/// `from-csv [-s SEP] [--no-header] [FILE]`
+pub struct FromCsv;
+
+impl StructuredBuiltin for FromCsv {
+    fn name(&self) -> &'static str {
+        "from-csv"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let (options, files) = CsvOptions::parse(args)?;
+        if files.len() > 1 {
+            return Err(invalid("usage: from-csv [-s SEP] [--no-header] [FILE]"));
+        }
+        let mut records = CsvReader {
+            reader: text_source(ctx, files.first(), input)?,
+            separator: options.separator,
+        };
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut rows = Vec::new();
+        while let Some(record) = records.next_record()? {
+            if options.header && columns.is_empty() && rows.is_empty() {
+                columns = record;
+                continue;
+            }
+            while columns.len() < record.len() {
+                columns.push(format!("column{}", columns.len() + 1));
+            }
+            let row: HashMap<String, StructuredValue> = columns
+                .iter()
+                .cloned()
+                .zip(record.iter().map(|cell| csv_value(cell)))
+                .collect();
+            rows.push(row);
+        }
+        Ok(PipelineData::new(StructuredValue::Table(rows)).with_columns(&columns))
+    }
+}
+
+/// `to-csv [-s SEP] [--no-header]`
+pub struct ToCsv;
+
+impl StructuredBuiltin for ToCsv {
+    fn name(&self) -> &'static str {
+        "to-csv"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let (options, extra) = CsvOptions::parse(args)?;
+        if let Some(arg) = extra.first() {
+            return Err(invalid(format!("{arg}: unexpected argument")));
+        }
+        let data = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        if !data.is_tabular() {
+            return Err(invalid(format!(
+                "expects a table, got {}",
+                data.value.type_name()
+            )));
+        }
+        let (headers, rows) = data.cells();
+        let mut out = String::new();
+        let records = options.header.then_some(&headers).into_iter().chain(&rows);
+        for record in records {
+            let fields: Vec<String> = record
+                .iter()
+                .map(|field| csv_quote(field, options.separator))
+                .collect();
+            out.push_str(&fields.join(&options.separator.to_string()));
+            out.push('\n');
+        }
+        Ok(PipelineData::new(StructuredValue::String(out)))
+    }
+}
+
+/// `from-yaml [FILE]`
+pub struct FromYaml;
+
+impl StructuredBuiltin for FromYaml {
+    fn name(&self) -> &'static str {
+        "from-yaml"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if args.len() > 1 {
+            return Err(invalid("usage: from-yaml [FILE]"));
+        }
+        let text = read_text(text_source(ctx, args.first(), input)?)?;
+        let mut documents = Vec::new();
+        for document in serde_yaml::Deserializer::from_str(&text) {
+            documents.push(
+                Document::deserialize(document)
+                    .map_err(|e| invalid(format!("invalid YAML: {e}")))?,
+            );
+        }
+        Ok(with_comments(
+            document_data(Document::stream(documents)),
+            &text,
+        ))
+    }
+}
+
+/// `to-yaml`
+pub struct ToYaml;
+
+impl StructuredBuiltin for ToYaml {
+    fn name(&self) -> &'static str {
+        "to-yaml"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if let Some(arg) = args.first() {
+            return Err(invalid(format!("{arg}: unexpected argument")));
+        }
+        let data = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let order = KeyOrder::from_data(&data);
+        let mut out = data.metadata.get(COMMENTS).cloned().unwrap_or_default();
+        YamlWriter {
+            order: &order,
+            out: &mut out,
+        }
+        .document(&data.value);
+        Ok(PipelineData::new(StructuredValue::String(out)))
+    }
+}
+
+/// `from-toml [FILE]`
+pub struct FromToml;
+
+impl StructuredBuiltin for FromToml {
+    fn name(&self) -> &'static str {
+        "from-toml"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if args.len() > 1 {
+            return Err(invalid("usage: from-toml [FILE]"));
+        }
+        let text = read_text(text_source(ctx, args.first(), input)?)?;
+        let document: Document =
+            toml::from_str(&text).map_err(|e| invalid(format!("invalid TOML: {}", e.message())))?;
+        Ok(with_comments(document_data(document), &text))
+    }
+}
+
+/// `to-toml`
+pub struct ToToml;
+
+impl StructuredBuiltin for ToToml {
+    fn name(&self) -> &'static str {
+        "to-toml"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if let Some(arg) = args.first() {
+            return Err(invalid(format!("{arg}: unexpected argument")));
+        }
+        let data = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let StructuredValue::Record(fields) = &data.value else {
+            return Err(invalid(format!(
+                "expects a record, got {}",
+                data.value.type_name()
+            )));
+        };
+        let order = KeyOrder::from_data(&data);
+        let mut out = data.metadata.get(COMMENTS).cloned().unwrap_or_default();
+        TomlWriter {
+            order: &order,
+            out: &mut out,
+        }
+        .table(fields, "", &[]);
+        Ok(PipelineData::new(StructuredValue::String(out)))
+    }
+}
+
+fn read_text(mut reader: Box<dyn BufRead>) -> ShellResult<String> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|e| invalid(e.to_string()))?;
+    Ok(text)
+}
+
+/// Keep the comment lines `text` starts with, for `to-yaml` and `to-toml`
+fn with_comments(data: PipelineData, text: &str) -> PipelineData {
+    let comments: String = text
+        .lines()
+        .take_while(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect();
+    if comments.is_empty() {
+        data
+    } else {
+        data.add_metadata(COMMENTS.to_string(), comments)
+    }
+}
+
+struct CsvOptions {
+    separator: char,
+    header: bool,
+}
+
+impl CsvOptions {
+    /// Options, and the operands after them
+    fn parse(args: &[String]) -> ShellResult<(Self, &[String])> {
+        let mut options = CsvOptions {
+            separator: ',',
+            header: true,
+        };
+        let mut rest = args;
+        while let Some(flag) = rest.first() {
+            match flag.as_str() {
+                "-s" | "--separator" => {
+                    let mut chars = rest.get(1).map(|sep| sep.chars()).into_iter().flatten();
+                    options.separator = match (chars.next(), chars.next()) {
+                        (Some('\\'), Some('t')) => '\t',
+                        (Some(sep), None) if sep != '"' => sep,
+                        _ => return Err(invalid("--separator: expects one character")),
+                    };
+                    rest = &rest[1..];
+                }
+                "--no-header" => options.header = false,
+                "--" => {
+                    rest = &rest[1..];
+                    break;
+                }
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Err(invalid(format!("{f}: invalid option")))
+                }
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+        Ok((options, rest))
+    }
+}
+
+/// Reads CSV records one at a time; quoted fields may span lines
+struct CsvReader {
+    reader: Box<dyn BufRead>,
+    separator: char,
+}
+
+impl CsvReader {
+    fn next_record(&mut self) -> ShellResult<Option<Vec<String>>> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut started = false;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self
+                .reader
+                .read_line(&mut line)
+                .map_err(|e| invalid(e.to_string()))?;
+            if read == 0 {
+                if quoted {
+                    return Err(invalid("unterminated quoted field"));
+                }
+                if !started {
+                    return Ok(None);
+                }
+                break;
+            }
+            let text = line.trim_end_matches(['\n', '\r']);
+            // Blank lines between records are skipped
+            if !started && text.is_empty() {
+                continue;
+            }
+            started = true;
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' if quoted => quoted = false,
+                    '"' if field.is_empty() => quoted = true,
+                    c if c == self.separator && !quoted => fields.push(std::mem::take(&mut field)),
+                    c => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            field.push('\n');
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+/// A CSV cell as the most specific value it reads as
+fn csv_value(cell: &str) -> StructuredValue {
+    if cell.is_empty() {
+        StructuredValue::Nothing
+    } else if let Ok(i) = cell.parse::<i64>() {
+        StructuredValue::Int(i)
+    } else if let Ok(f) = cell.parse::<f64>() {
+        StructuredValue::Float(f)
+    } else {
+        match cell {
+            "true" => StructuredValue::Bool(true),
+            "false" => StructuredValue::Bool(false),
+            _ => StructuredValue::String(cell.to_string()),
+        }
+    }
+}
+
+fn csv_quote(field: &str, separator: char) -> String {
+    if field.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A parsed JSON, YAML or TOML value, keeping the order of object keys
+#[derive(Debug, Clone, PartialEq)]
+enum Document {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A TOML date or time, as written
+    Date(String),
+    Array(Vec<Document>),
+    Object(Vec<(String, Document)>),
+}
+
+/// The key under which the `toml` deserializer hands over a date or time
+const TOML_DATETIME: &str = "$__toml_private_datetime";
+
+impl Document {
+    /// One value for a whole stream: nothing, the single value, or a list
+    fn stream(mut documents: Vec<Document>) -> Self {
+        match documents.len() {
+            0 => Document::Null,
+            1 => documents.pop().unwrap_or(Document::Null),
+            _ => Document::Array(documents),
+        }
+    }
+
+    /// Keys of an object, or of the objects in an array in first-seen order
+    fn columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = Vec::new();
+        let mut add = |fields: &[(String, Document)]| {
+            for (key, _) in fields {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        };
+        match self {
+            Document::Object(fields) => add(fields),
+            Document::Array(items) => {
+                for item in items {
+                    if let Document::Object(fields) = item {
+                        add(fields);
+                    }
+                }
+            }
+            _ => {}
+        }
+        columns
+    }
+
+    fn into_structured(self) -> StructuredValue {
+        match self {
+            Document::Null => StructuredValue::Nothing,
+            Document::Bool(b) => StructuredValue::Bool(b),
+            Document::Int(i) => StructuredValue::Int(i),
+            Document::Float(f) => StructuredValue::Float(f),
+            Document::String(s) => StructuredValue::String(s),
+            // Local dates and times have no time zone to be a `Date` with
+            Document::Date(text) => DateTime::parse_from_rfc3339(&text)
+                .map(|date| StructuredValue::Date(date.with_timezone(&Utc)))
+                .unwrap_or(StructuredValue::String(text)),
+            Document::Object(fields) => StructuredValue::Record(Self::record(fields)),
+            // An array of objects is a table
+            Document::Array(items)
+                if !items.is_empty() && items.iter().all(|i| matches!(i, Document::Object(_))) =>
+            {
+                StructuredValue::Table(
+                    items
+                        .into_iter()
+                        .map(|item| match item {
+                            Document::Object(fields) => Self::record(fields),
+                            _ => HashMap::new(),
+                        })
+                        .collect(),
+                )
+            }
+            Document::Array(items) => {
+                StructuredValue::List(items.into_iter().map(Document::into_structured).collect())
+            }
+        }
+    }
+
+    fn record(fields: Vec<(String, Document)>) -> Row {
+        fields
+            .into_iter()
+            .map(|(key, value)| (key, value.into_structured()))
+            .collect()
+    }
+}
+
+/// A parsed document as pipeline data, remembering its key order
+fn document_data(document: Document) -> PipelineData {
+    let columns = document.columns();
+    let order = KeyOrder::of(&document);
+    let data = order.attach(PipelineData::new(document.into_structured()));
+    if columns.is_empty() {
+        data
+    } else {
+        data.with_columns(&columns)
+    }
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DocumentVisitor)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON, YAML or TOML value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Document, E> {
+        Ok(Document::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Document, E> {
+        Ok(Document::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Document, E> {
+        Ok(Document::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Document, E> {
+        Ok(i64::try_from(v).map_or(Document::Float(v as f64), Document::Int))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Document, E> {
+        Ok(Document::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Document, E> {
+        Ok(Document::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Document, E> {
+        Ok(Document::String(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Document, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Document::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Document, A::Error> {
+        let mut fields: Vec<(String, Document)> = Vec::new();
+        while let Some(Key(key)) = map.next_key()? {
+            let value: Document = map.next_value()?;
+            if key == TOML_DATETIME {
+                if let Document::String(text) = value {
+                    return Ok(Document::Date(text));
+                }
+            }
+            // A repeated key keeps its first position and its last value
+            match fields.iter_mut().find(|(existing, _)| *existing == key) {
+                Some(field) => field.1 = value,
+                None => fields.push((key, value)),
+            }
+        }
+        Ok(Document::Object(fields))
+    }
+}
+
+/// An object key; YAML also allows numbers, booleans and null as keys
+struct Key(String);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl Visitor<'_> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string, number or boolean key")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Key, E> {
+        Ok(Key("null".to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Key, E> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Key, E> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Key, E> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Key, E> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Key, E> {
+        Ok(Key(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Key, E> {
+        Ok(Key(v))
+    }
+}
+
+/// The order of each object's keys, by path: `""` is the top level, `a.b`
+/// the object under key `b` of `a`, and `a[]` the objects in the array `a`
+#[derive(Debug, Default)]
+pub(crate) struct KeyOrder(HashMap<String, Vec<String>>);
+
+impl KeyOrder {
+    fn of(document: &Document) -> Self {
+        let mut order = Self::default();
+        order.collect(document, "");
+        order
+    }
+
+    fn collect(&mut self, document: &Document, path: &str) {
+        match document {
+            Document::Object(fields) => {
+                let keys = self.0.entry(path.to_string()).or_default();
+                for (key, _) in fields {
+                    if !keys.contains(key) {
+                        keys.push(key.clone());
+                    }
+                }
+                for (key, value) in fields {
+                    self.collect(value, &child(path, key));
+                }
+            }
+            Document::Array(items) => {
+                for item in items {
+                    self.collect(item, &element(path));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The order recorded with `data`; filters keep only the column order,
+    /// which then still orders the top level
+    pub(crate) fn from_data(data: &PipelineData) -> Self {
+        let mut order: HashMap<String, Vec<String>> = data
+            .metadata
+            .get(KEY_ORDER)
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+        match data.value {
+            StructuredValue::Record(_) => order.insert(String::new(), data.columns()),
+            StructuredValue::Table(_) => order.insert(element(""), data.columns()),
+            _ => None,
+        };
+        Self(order)
+    }
+
+    pub(crate) fn attach(&self, data: PipelineData) -> PipelineData {
+        match serde_json::to_string(&self.0) {
+            Ok(json) => data.add_metadata(KEY_ORDER.to_string(), json),
+            Err(_) => data,
+        }
+    }
+
+    /// The order below `path`, with paths made relative to it
+    pub(crate) fn subtree(&self, path: &str) -> Self {
+        let order = self
+            .0
+            .iter()
+            .filter_map(|(key, keys)| {
+                let rest = key.strip_prefix(path)?;
+                let rest = match rest.strip_prefix('.') {
+                    Some(rest) if !path.is_empty() => rest,
+                    _ if rest.is_empty() || rest.starts_with('[') || path.is_empty() => rest,
+                    _ => return None,
+                };
+                Some((rest.to_string(), keys.clone()))
+            })
+            .collect();
+        Self(order)
+    }
+
+    /// The recorded keys of the object at `path`
+    pub(crate) fn columns(&self, path: &str) -> Option<&[String]> {
+        self.0.get(path).map(Vec::as_slice)
+    }
+
+    /// The keys of the object at `path`: recorded ones first, then the rest
+    /// by name
+    pub(crate) fn keys<'a>(&self, path: &str, fields: &'a Row) -> Vec<&'a String> {
+        let mut keys: Vec<&String> = self
+            .0
+            .get(path)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| fields.get_key_value(key).map(|(key, _)| key))
+            .collect();
+        let mut others: Vec<&String> = fields.keys().filter(|key| !keys.contains(key)).collect();
+        others.sort();
+        keys.extend(others);
+        keys
+    }
+}
+
+pub(crate) fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+pub(crate) fn element(path: &str) -> String {
+    format!("{path}[]")
+}
+
+/// A value as YAML and TOML can hold it: paths and ranges as text,
+/// durations as seconds and binary data as a list of bytes
+fn plain(value: &StructuredValue) -> Cow<'_, StructuredValue> {
+    match value {
+        StructuredValue::Path(path) => {
+            Cow::Owned(StructuredValue::String(path.display().to_string()))
+        }
+        StructuredValue::Range { .. } => Cow::Owned(StructuredValue::String(value.to_string())),
+        StructuredValue::Duration(d) => Cow::Owned(StructuredValue::Int(d.num_seconds())),
+        StructuredValue::Binary(bytes) => Cow::Owned(StructuredValue::List(
+            bytes
+                .iter()
+                .map(|b| StructuredValue::Int((*b).into()))
+                .collect(),
+        )),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+/// A float that reads back as one: `1.0` rather than `1`
+fn float_text(f: f64) -> String {
+    let text = f.to_string();
+    if text.contains(['.', 'e', 'E']) {
+        text
+    } else {
+        format!("{text}.0")
+    }
+}
+
+/// Writes a structured value as JSON, pretty with `indent` spaces or compact
+struct JsonWriter<'a> {
+    indent: Option<usize>,
+    order: &'a KeyOrder,
+    out: &'a mut String,
+}
+
+impl JsonWriter<'_> {
+    fn write(&mut self, value: &StructuredValue, path: &str, depth: usize) {
+        match value {
+            StructuredValue::Nothing => self.out.push_str("null"),
+            StructuredValue::Bool(b) => self.out.push_str(&b.to_string()),
+            StructuredValue::Int(i) => self.out.push_str(&i.to_string()),
+            StructuredValue::Float(f) if f.is_finite() => self.out.push_str(&f.to_string()),
+            StructuredValue::Float(_) => self.out.push_str("null"),
+            StructuredValue::String(s) => self.string(s),
+            StructuredValue::Date(date) => self.string(&date.to_rfc3339()),
+            StructuredValue::Path(path) => self.string(&path.display().to_string()),
+            StructuredValue::Duration(d) => self.out.push_str(&d.num_seconds().to_string()),
+            StructuredValue::Binary(_) => self.write(&plain(value), path, depth),
+            StructuredValue::Range { start, end, step } => {
+                let (start, end, step) = (
+                    StructuredValue::Int(*start),
+                    StructuredValue::Int(*end),
+                    StructuredValue::Int(*step),
+                );
+                let entries = vec![("start", &start), ("end", &end), ("step", &step)];
+                self.entries(entries, path, depth);
+            }
+            StructuredValue::List(items) => {
+                self.open('[');
+                for (i, item) in items.iter().enumerate() {
+                    self.separator(i, depth + 1);
+                    self.write(item, &element(path), depth + 1);
+                }
+                self.close(']', items.is_empty(), depth);
+            }
+            StructuredValue::Record(fields) => self.object(fields, path, depth),
+            StructuredValue::Table(rows) => {
+                self.open('[');
+                for (i, row) in rows.iter().enumerate() {
+                    self.separator(i, depth + 1);
+                    self.object(row, &element(path), depth + 1);
+                }
+                self.close(']', rows.is_empty(), depth);
+            }
+        }
+    }
+
+    fn object(&mut self, fields: &Row, path: &str, depth: usize) {
+        let entries = self
+            .order
+            .keys(path, fields)
+            .into_iter()
+            .map(|key| (key.as_str(), &fields[key]))
+            .collect();
+        self.entries(entries, path, depth);
+    }
+
+    fn entries(&mut self, entries: Vec<(&str, &StructuredValue)>, path: &str, depth: usize) {
+        self.open('{');
+        for (i, (key, value)) in entries.iter().enumerate() {
+            self.separator(i, depth + 1);
+            self.string(key);
+            self.out.push(':');
+            if self.indent.is_some() {
+                self.out.push(' ');
+            }
+            self.write(value, &child(path, key), depth + 1);
+        }
+        self.close('}', entries.is_empty(), depth);
+    }
+
+    fn string(&mut self, s: &str) {
+        // Serializing a str cannot fail
+        self.out
+            .push_str(&serde_json::to_string(s).unwrap_or_default());
+    }
+
+    fn open(&mut self, bracket: char) {
+        self.out.push(bracket);
+    }
+
+    /// Comma before every item but the first, then the item's indentation
+    fn separator(&mut self, index: usize, depth: usize) {
+        if index > 0 {
+            self.out.push(',');
+        }
+        self.newline(depth);
+    }
+
+    fn close(&mut self, bracket: char, empty: bool, depth: usize) {
+        if !empty {
+            self.newline(depth);
+        }
+        self.out.push(bracket);
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(width) = self.indent {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(width * depth));
+        }
+    }
+}
+
+/// Writes a structured value as block style YAML
+struct YamlWriter<'a> {
+    order: &'a KeyOrder,
+    out: &'a mut String,
+}
+
+impl YamlWriter<'_> {
+    fn document(&mut self, value: &StructuredValue) {
+        match &*plain(value) {
+            StructuredValue::Record(fields) if !fields.is_empty() => {
+                self.mapping(fields, "", 0, false)
+            }
+            StructuredValue::Table(rows) if !rows.is_empty() => self.table(rows, "", 0, false),
+            StructuredValue::List(items) if !items.is_empty() => self.list(items, "", 0, false),
+            other => {
+                self.scalar(other);
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// Write the value of a `key:` or `-` line indented by `indent`; a
+    /// collection under `-` starts on the same line
+    fn value(&mut self, value: &StructuredValue, path: &str, indent: usize, after_dash: bool) {
+        let value = plain(value);
+        let nested = match &*value {
+            StructuredValue::Record(fields) => !fields.is_empty(),
+            StructuredValue::Table(rows) => !rows.is_empty(),
+            StructuredValue::List(items) => !items.is_empty(),
+            _ => false,
+        };
+        if !nested {
+            self.out.push(' ');
+            self.scalar(&value);
+            self.out.push('\n');
+            return;
+        }
+        self.out.push(if after_dash { ' ' } else { '\n' });
+        match &*value {
+            StructuredValue::Record(fields) => self.mapping(fields, path, indent + 2, after_dash),
+            StructuredValue::Table(rows) => self.table(rows, path, indent + 2, after_dash),
+            StructuredValue::List(items) => self.list(items, path, indent + 2, after_dash),
+            _ => {}
+        }
+    }
+
+    /// `inline` means the first line continues one already started
+    fn mapping(&mut self, fields: &Row, path: &str, indent: usize, inline: bool) {
+        for (i, key) in self.order.keys(path, fields).into_iter().enumerate() {
+            self.indent(indent, inline && i == 0);
+            self.out.push_str(&yaml_string(key));
+            self.out.push(':');
+            self.value(&fields[key], &child(path, key), indent, false);
+        }
+    }
+
+    fn list(&mut self, items: &[StructuredValue], path: &str, indent: usize, inline: bool) {
+        for (i, item) in items.iter().enumerate() {
+            self.indent(indent, inline && i == 0);
+            self.out.push('-');
+            self.value(item, &element(path), indent, true);
+        }
+    }
+
+    fn table(&mut self, rows: &[Row], path: &str, indent: usize, inline: bool) {
+        for (i, row) in rows.iter().enumerate() {
+            self.indent(indent, inline && i == 0);
+            if row.is_empty() {
+                self.out.push_str("- {}\n");
+            } else {
+                self.out.push_str("- ");
+                self.mapping(row, &element(path), indent + 2, true);
+            }
+        }
+    }
+
+    fn indent(&mut self, indent: usize, inline: bool) {
+        if !inline {
+            self.out.push_str(&" ".repeat(indent));
+        }
+    }
+
+    fn scalar(&mut self, value: &StructuredValue) {
+        let text = match value {
+            StructuredValue::Nothing => "null".to_string(),
+            StructuredValue::Float(f) if f.is_nan() => ".nan".to_string(),
+            StructuredValue::Float(f) if f.is_infinite() => {
+                if *f > 0.0 { ".inf" } else { "-.inf" }.to_string()
+            }
+            StructuredValue::Float(f) => float_text(*f),
+            StructuredValue::String(s) => yaml_string(s),
+            StructuredValue::Date(date) => date.to_rfc3339(),
+            StructuredValue::Record(_) => "{}".to_string(),
+            StructuredValue::Table(_) | StructuredValue::List(_) => "[]".to_string(),
+            other => other.to_string(),
+        };
+        self.out.push_str(&text);
+    }
+}
+
+/// A string as plain YAML when it cannot be read as anything else, otherwise
+/// double quoted
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`', '.', '+', '~', '<', '=',
+        ])
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':')
+        && !s.chars().any(char::is_control)
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n"
+        );
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).unwrap_or_default()
+    }
+}
+
+/// Writes a record as a TOML document: plain keys first, then a `[table]`
+/// section per nested record and an `[[array]]` section per table row
+struct TomlWriter<'a> {
+    order: &'a KeyOrder,
+    out: &'a mut String,
+}
+
+impl TomlWriter<'_> {
+    fn table(&mut self, fields: &Row, path: &str, header: &[String]) {
+        let keys = self.order.keys(path, fields);
+        let mut sections = Vec::new();
+        for key in keys {
+            let value = plain(&fields[key]);
+            match &*value {
+                // TOML has no null; the key is left out
+                StructuredValue::Nothing => {}
+                StructuredValue::Record(inner) if !inner.is_empty() => sections.push(key),
+                value if !toml_rows(value).is_empty() => sections.push(key),
+                value => {
+                    self.out.push_str(&toml_key(key));
+                    self.out.push_str(" = ");
+                    self.inline(value, &child(path, key));
+                    self.out.push('\n');
+                }
+            }
+        }
+
+        for key in sections {
+            let mut name = header.to_vec();
+            name.push(key.clone());
+            let dotted: Vec<String> = name.iter().map(|part| toml_key(part)).collect();
+            let path = child(path, key);
+            match &fields[key] {
+                StructuredValue::Record(inner) => {
+                    self.section_break();
+                    self.out.push_str(&format!("[{}]\n", dotted.join(".")));
+                    self.table(inner, &path, &name);
+                }
+                rows => {
+                    for row in toml_rows(rows) {
+                        self.section_break();
+                        self.out.push_str(&format!("[[{}]]\n", dotted.join(".")));
+                        self.table(row, &element(&path), &name);
+                    }
+                }
+            }
+        }
+    }
+
+    fn section_break(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn inline(&mut self, value: &StructuredValue, path: &str) {
+        match &*plain(value) {
+            StructuredValue::Float(f) if f.is_nan() => self.out.push_str("nan"),
+            StructuredValue::Float(f) if f.is_infinite() => {
+                self.out.push_str(if *f > 0.0 { "inf" } else { "-inf" })
+            }
+            StructuredValue::Float(f) => self.out.push_str(&float_text(*f)),
+            StructuredValue::String(s) => self
+                .out
+                .push_str(&serde_json::to_string(s).unwrap_or_default()),
+            StructuredValue::Date(date) => self.out.push_str(&date.to_rfc3339()),
+            StructuredValue::List(items) => {
+                let items: Vec<&StructuredValue> = items
+                    .iter()
+                    .filter(|item| !matches!(item, StructuredValue::Nothing))
+                    .collect();
+                self.out.push('[');
+                for (i, item) in items.into_iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.inline(item, &element(path));
+                }
+                self.out.push(']');
+            }
+            StructuredValue::Table(rows) => {
+                self.out.push('[');
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.inline_table(row, &element(path));
+                }
+                self.out.push(']');
+            }
+            StructuredValue::Record(fields) => self.inline_table(fields, path),
+            other => self.out.push_str(&other.to_string()),
+        }
+    }
+
+    fn inline_table(&mut self, fields: &Row, path: &str) {
+        let keys: Vec<&String> = self
+            .order
+            .keys(path, fields)
+            .into_iter()
+            .filter(|key| !matches!(fields[*key], StructuredValue::Nothing))
+            .collect();
+        if keys.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{ ");
+        for (i, key) in keys.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&toml_key(key));
+            self.out.push_str(" = ");
+            self.inline(&fields[key], &child(path, key));
+        }
+        self.out.push_str(" }");
+    }
+}
+
+/// The rows of a table, or of a list holding only records, written as an
+/// array of tables
+fn toml_rows(value: &StructuredValue) -> Vec<&Row> {
+    match value {
+        StructuredValue::Table(rows) => rows.iter().collect(),
+        StructuredValue::List(items) => items
+            .iter()
+            .map(|item| match item {
+                StructuredValue::Record(fields) => Some(fields),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// A bare key when it may be one, otherwise quoted
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        serde_json::to_string(key).unwrap_or_default()
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ShellError {
+    ShellError::new(
+        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
+        message,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_records(text: &str) -> Vec<Vec<String>> {
+        let mut reader = CsvReader {
+            reader: Box::new(std::io::Cursor::new(text.to_string())),
+            separator: ',',
+        };
+        let mut records = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn csv_quotes_span_separators_and_lines() {
+        assert_eq!(
+            csv_records("a,\"b,c\",\"say \"\"hi\"\"\"\n\n\"multi\nline\",,x\n"),
+            vec![vec!["a", "b,c", "say \"hi\""], vec!["multi\nline", "", "x"],]
+        );
+        assert_eq!(csv_quote("b,c", ','), "\"b,c\"");
+        assert_eq!(csv_quote("plain", ','), "plain");
+    }
+
+    fn json(value: &StructuredValue, order: &KeyOrder, indent: Option<usize>) -> String {
+        let mut out = String::new();
+        JsonWriter {
+            indent,
+            order,
+            out: &mut out,
+        }
+        .write(value, "", 0);
+        out
+    }
+
+    #[test]
+    fn json_keeps_key_order_and_round_trips() {
+        let document: Document = serde_json::from_str(
+            r#"[{"z": 1, "a": "x"}, {"a": null, "m": 2.5, "n": {"q": 1, "p": [{"y": 0, "x": 0}]}}]"#,
+        )
+        .unwrap();
+        assert_eq!(document.columns(), vec!["z", "a", "m", "n"]);
+
+        let order = KeyOrder::of(&document);
+        let value = document.into_structured();
+        assert_eq!(
+            json(&value, &order, None),
+            r#"[{"z":1,"a":"x"},{"a":null,"m":2.5,"n":{"q":1,"p":[{"y":0,"x":0}]}}]"#
+        );
+
+        let list = StructuredValue::List(vec![StructuredValue::Int(1)]);
+        assert_eq!(json(&list, &KeyOrder::default(), Some(2)), "[\n  1\n]");
+    }
+
+    #[test]
+    fn yaml_round_trips_in_order() {
+        let text = "name: nxsh\nversion: 1.0\ndeps:\n- name: serde\n  tags: [a, b]\n- {}\nempty: []\nnote: 'yes'\n";
+        let document = Document::deserialize(serde_yaml::Deserializer::from_str(text)).unwrap();
+        let order = KeyOrder::of(&document);
+        let mut out = String::new();
+        YamlWriter {
+            order: &order,
+            out: &mut out,
+        }
+        .document(&document.into_structured());
+        assert_eq!(
+            out,
+            "name: nxsh\nversion: 1.0\ndeps:\n  - name: serde\n    tags:\n      - a\n      - b\n  - {}\nempty: []\nnote: \"yes\"\n"
+        );
+    }
+
+    #[test]
+    fn toml_writes_sections_after_keys() {
+        let text = "title = \"x\"\nwhen = 1979-05-27T07:32:00Z\n\n[owner]\nname = \"Tom\"\nratio = 2.0\n\n[[bin]]\nname = \"a\"\n\n[[bin]]\nname = \"b c\"\n";
+        let document: Document = toml::from_str(text).unwrap();
+        assert!(
+            matches!(&document, Document::Object(fields) if fields[1].1 == Document::Date("1979-05-27T07:32:00Z".into()))
+        );
+        let order = KeyOrder::of(&document);
+        let StructuredValue::Record(fields) = document.into_structured() else {
+            panic!("a TOML document is a record");
+        };
+        let mut out = String::new();
+        TomlWriter {
+            order: &order,
+            out: &mut out,
+        }
+        .table(&fields, "", &[]);
+        assert_eq!(
+            out,
+            "title = \"x\"\nwhen = 1979-05-27T07:32:00+00:00\n\n[owner]\nname = \"Tom\"\nratio = 2.0\n\n[[bin]]\nname = \"a\"\n\n[[bin]]\nname = \"b c\"\n"
+        );
+        assert_eq!(toml_key("a b"), "\"a b\"");
+    }
+}
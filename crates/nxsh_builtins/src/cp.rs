@@ -4,11 +4,30 @@
 //!   cp -r SRC_DIR DST_DIR
 //!   cp -p SRC DST (preserve permissions and timestamps)
 //!   cp -v SRC DST (verbose output)
-
+//!   cp -i|-n|-u SRC DST (ask before, never or only for older overwrites)
+//!   cp -b|--backup[=CONTROL] [-S SUFFIX] SRC DST (back up what is overwritten)
+//!   cp -r -j N SRC_DIR DST_DIR (copy N files at a time)
+//!   cp --reflink[=WHEN] --sparse=WHEN SRC DST (clone blocks, keep holes)
+//!
+//! Files are copied through `nxsh_hal::copy`, which clones them where the
+//! file system can and otherwise has the kernel copy them. The operands
+//! are walked first, creating directories and asking about overwrites,
+//! and the files found are then copied by `--jobs` threads while the
+//! progress of the whole copy is drawn on standard error, under
+//! `--progress` or on a terminal when the copy is large.
+
+use crate::common::hal_message;
+use crate::safety::{BackupMode, Clobber, Overwrite, Prompter};
+use crate::transfer::{self, Transfer};
 use anyhow::{anyhow, Context, Result};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::copy::{Reflink, Sparse};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
 use std::time::SystemTime;
 use tracing::{debug, info, warn};
 
@@ -18,41 +37,6 @@ use sha2::{Digest, Sha256};
 #[cfg(windows)]
 use std::os::windows::fs::OpenOptionsExt;
 
-// Progress tracking for large operations
-struct ProgressTracker {
-    total_files: u64,
-    processed_files: u64,
-    show_progress: bool,
-}
-
-impl ProgressTracker {
-    fn new(total_files: u64, show_progress: bool) -> Self {
-        Self {
-            total_files,
-            processed_files: 0,
-            show_progress,
-        }
-    }
-
-    fn increment(&mut self) {
-        self.processed_files += 1;
-        if self.show_progress && self.total_files > 0 {
-            let percentage = (self.processed_files * 100) / self.total_files;
-            print!(
-                "\rCopying files: {}/{} ({}%)",
-                self.processed_files, self.total_files, percentage
-            );
-            io::stdout().flush().unwrap_or(());
-        }
-    }
-
-    fn finish(&self) {
-        if self.show_progress {
-            println!("\nCopy completed: {} files processed", self.processed_files);
-        }
-    }
-}
-
 /// Copy options for controlling behavior
 /// Print help information for the cp command
 fn print_cp_help() {
@@ -72,8 +56,13 @@ fn print_cp_help() {
     println!("    -P, --no-dereference     Never follow symbolic links");
     println!("    -n, --no-clobber         Do not overwrite existing files");
     println!("    -i, --interactive        Prompt before overwriting files");
-    println!("    -b, --backup             Make backup of existing destination files");
+    println!("    -b, --backup[=CONTROL]   Make backup of existing destination files");
+    println!("    -S, --suffix=SUFFIX      Suffix of simple backups (default '~')");
     println!("    -t, --target-directory   Copy all sources into DIRECTORY");
+    println!("    -j, --jobs N             Copy up to N files at a time (default 1)");
+    println!("    --reflink[=WHEN]         Clone file blocks: auto, always or never");
+    println!("    --sparse=WHEN            Make holes in the copy: auto, always or never");
+    println!("    --progress               Show the bytes copied, the rate and the time left");
     println!();
     println!("Windows-specific options:");
     println!("    --preserve-acl           Preserve Access Control Lists (ACLs)");
@@ -99,12 +88,226 @@ struct CopyOptions {
     preserve_ads: bool, // Alternate Data Streams
     preserve_compression: bool,
     retry_count: u32,
+    overwrite: Overwrite,
+    /// How many files to copy at a time
+    jobs: usize,
+    reflink: Reflink,
+    sparse: Sparse,
+}
+
+/// A file waiting to be copied
+struct Queued {
+    src: PathBuf,
+    dst: PathBuf,
+    size: u64,
+    /// How the source and destination are named to the user
+    shown: (String, String),
+}
+
+/// The questions and output of one run
+struct Session<'s, 'a> {
+    prompter: &'s mut Prompter<'a>,
+    cwd: PathBuf,
+    stdout: String,
+    /// The files to copy once the operands have been walked
+    queue: Vec<Queued>,
+}
+
+impl<'s, 'a> Session<'s, 'a> {
+    fn new(prompter: &'s mut Prompter<'a>, cwd: PathBuf) -> Self {
+        Session {
+            prompter,
+            cwd,
+            stdout: String::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    /// How to name `path` to the user: relative to the working directory
+    /// when it is under it, as the operands usually are
+    fn shown(&self, path: &Path) -> String {
+        path.strip_prefix(&self.cwd)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// Whether `src` may be copied over `dst`, asking and backing `dst` up
+    /// as the options say
+    fn may_overwrite(&mut self, src: &Path, dst: &Path, options: &CopyOptions) -> Result<bool> {
+        let shown = self.shown(dst);
+        options
+            .overwrite
+            .allow(src, dst, &shown, self.prompter)
+            .map_err(|message| anyhow!("cp: {message}"))
+    }
+
+    /// Copy the queued files, `options.jobs` at a time, drawing the
+    /// progress of the whole copy when it is shown, and name the copies
+    /// under `-v` in the order they were queued. After a failure no more
+    /// copies start, and the first failure is the error.
+    fn copy_queued(&mut self, options: &CopyOptions) -> Result<()> {
+        let queue = std::mem::take(&mut self.queue);
+        if queue.is_empty() {
+            return Ok(());
+        }
+        let total: u64 = queue.iter().map(|file| file.size).sum();
+        let show =
+            options.show_progress || (self.prompter.on_terminal() && total >= transfer::LARGE);
+
+        let next = AtomicUsize::new(0);
+        let latest = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let done = AtomicU64::new(0);
+        let copied: Vec<AtomicU64> = queue.iter().map(|_| AtomicU64::new(0)).collect();
+        let results: Vec<Mutex<Option<Result<()>>>> =
+            queue.iter().map(|_| Mutex::new(None)).collect();
+        let work = || {
+            while !failed.load(Ordering::Relaxed) {
+                let n = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = queue.get(n) else {
+                    break;
+                };
+                latest.store(n, Ordering::Relaxed);
+                let mut progress = |bytes: u64| {
+                    copied[n].fetch_add(bytes, Ordering::Relaxed);
+                    done.fetch_add(bytes, Ordering::Relaxed);
+                };
+                let result = copy_file_with_progress(&file.src, &file.dst, options, &mut progress)
+                    .with_context(|| {
+                        format!(
+                            "Failed to copy file '{}' to '{}'",
+                            file.shown.0, file.shown.1
+                        )
+                    });
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                *results[n].lock().unwrap_or_else(PoisonError::into_inner) = Some(result);
+            }
+        };
+
+        let workers = options.jobs.clamp(1, queue.len());
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(work)).collect();
+            if !show {
+                return;
+            }
+            let mut drawing = Transfer::new(total);
+            let out = self.prompter.out();
+            loop {
+                let finished = handles.iter().all(|handle| handle.is_finished());
+                let n = latest.load(Ordering::Relaxed);
+                let line = drawing.line(
+                    done.load(Ordering::Relaxed),
+                    &queue[n].shown.0,
+                    copied[n].load(Ordering::Relaxed),
+                    queue[n].size,
+                );
+                let _ = write!(out, "{line}");
+                if finished {
+                    let _ = writeln!(out);
+                    break;
+                }
+                let _ = out.flush();
+                std::thread::sleep(transfer::REDRAW);
+            }
+        });
+
+        let mut first_error = None;
+        for (file, result) in queue.iter().zip(results) {
+            match result.into_inner().unwrap_or_else(PoisonError::into_inner) {
+                Some(Ok(())) if options.verbose => {
+                    let line = format!("'{}' -> '{}'\n", file.shown.0, file.shown.1);
+                    self.stdout.push_str(&line);
+                }
+                Some(Err(e)) if first_error.is_none() => first_error = Some(e),
+                _ => {}
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+pub struct CpCommand;
+
+impl Builtin for CpCommand {
+    fn name(&self) -> &'static str {
+        "cp"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Copy files and directories"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy SOURCE to DEST, or several SOURCEs into DIRECTORY, asking before, \
+         skipping or backing up files that would be overwritten when told to."
+    }
+
+    fn usage(&self) -> &'static str {
+        "cp [-finprRuvb] [--backup[=CONTROL]] [-S SUFFIX] SOURCE... DEST"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy files and directories. Use 'cp -r src dst' for a directory, 'cp -i a b' \
+         to be asked before overwriting b, or 'cp -b a b' to keep the old b as b~."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let assume = ctx.get_var("NXSH_ASSUME");
+        let mut prompter = Prompter::new(
+            "cp",
+            assume.as_deref(),
+            ctx.interactive,
+            &mut ctx.stdin,
+            &mut ctx.stderr,
+        );
+        let mut session = Session::new(&mut prompter, ctx.cwd.clone());
+        let result = cp_impl(args, &mut session);
+        let stdout = session.stdout.into_bytes();
+        Ok(match result {
+            Ok(()) => ExecutionResult::success(0).with_output(stdout),
+            Err(e) => ExecutionResult::success(1)
+                .with_output(stdout)
+                .with_error(error_line(&e).into_bytes()),
+        })
+    }
+}
+
+/// An error as a line of standard error
+fn error_line(e: &anyhow::Error) -> String {
+    let message = format!("{e:#}");
+    if message.starts_with("cp: ") {
+        format!("{message}\n")
+    } else {
+        format!("cp: {message}\n")
+    }
+}
+
+/// Run cp on the process's own standard streams and working directory
+fn cp_on_stdio(args: &[String]) -> Result<()> {
+    let assume = std::env::var("NXSH_ASSUME").ok();
+    let terminal = io::stdin().is_terminal();
+    let mut input = io::stdin().lock();
+    let mut questions = io::stderr();
+    let mut prompter = Prompter::new(
+        "cp",
+        assume.as_deref(),
+        terminal,
+        &mut input,
+        &mut questions,
+    );
+    let mut session = Session::new(&mut prompter, std::env::current_dir()?);
+    let result = cp_impl(args, &mut session);
+    io::stdout().write_all(session.stdout.as_bytes())?;
+    result
 }
 
 // In super-min (size focused) build we compile a synchronous version to avoid pulling async runtime.
 #[cfg(feature = "super-min")]
 pub fn cp_cli(args: &[String]) -> Result<()> {
-    cp_impl(args)
+    cp_on_stdio(args)
 }
 
 // Default (non super-min) build keeps async for potential future async optimizations;
@@ -112,11 +315,11 @@ pub fn cp_cli(args: &[String]) -> Result<()> {
 // simplify and allow gating out Tokio entirely when async-runtime feature is absent.
 #[cfg(not(feature = "super-min"))]
 pub async fn cp_cli(args: &[String]) -> Result<()> {
-    cp_impl(args)
+    cp_on_stdio(args)
 }
 
 // Shared implementation (pure synchronous) used by both variants.
-fn cp_impl(args: &[String]) -> Result<()> {
+fn cp_impl(args: &[String], session: &mut Session<'_, '_>) -> Result<()> {
     if args.is_empty() {
         return Err(anyhow!("cp: missing operands"));
     }
@@ -124,8 +327,10 @@ fn cp_impl(args: &[String]) -> Result<()> {
     let mut options = CopyOptions::default();
     // First collect all non-flag operands, then validate count and split into sources/destination
     let mut operands: Vec<String> = Vec::new();
+    let mut suffix = None;
 
-    for arg in args {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
         if arg.starts_with("--") {
             // Long options
             match arg.as_str() {
@@ -142,6 +347,48 @@ fn cp_impl(args: &[String]) -> Result<()> {
                 "--preserve-acl" => options.preserve_acl = true,
                 "--preserve-ads" => options.preserve_ads = true,
                 "--preserve-compression" => options.preserve_compression = true,
+                "--force" => options.overwrite.clobber = Clobber::Replace,
+                "--interactive" => options.overwrite.clobber = Clobber::Ask,
+                "--no-clobber" => options.overwrite.clobber = Clobber::Keep,
+                "--update" => options.overwrite.update = true,
+                "--jobs" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("cp: option '--jobs' requires an argument"))?;
+                    options.jobs = parse_jobs(value)?;
+                }
+                arg if arg.starts_with("--jobs=") => {
+                    options.jobs = parse_jobs(arg.strip_prefix("--jobs=").unwrap())?;
+                }
+                "--reflink" => options.reflink = Reflink::Always,
+                arg if arg.starts_with("--reflink=") => {
+                    options.reflink = match arg.strip_prefix("--reflink=").unwrap() {
+                        "auto" => Reflink::Auto,
+                        "always" => Reflink::Always,
+                        "never" => Reflink::Never,
+                        when => {
+                            return Err(anyhow!("cp: invalid argument '{when}' for '--reflink'"))
+                        }
+                    };
+                }
+                arg if arg.starts_with("--sparse=") => {
+                    options.sparse = match arg.strip_prefix("--sparse=").unwrap() {
+                        "auto" => Sparse::Auto,
+                        "always" => Sparse::Always,
+                        "never" => Sparse::Never,
+                        when => {
+                            return Err(anyhow!("cp: invalid argument '{when}' for '--sparse'"))
+                        }
+                    };
+                }
+                "--backup" => options.overwrite.backup = BackupMode::from_env().map_err(cp_err)?,
+                arg if arg.starts_with("--backup=") => {
+                    let control = arg.strip_prefix("--backup=").unwrap();
+                    options.overwrite.backup = BackupMode::parse(control).map_err(cp_err)?;
+                }
+                arg if arg.starts_with("--suffix=") => {
+                    suffix = arg.strip_prefix("--suffix=").map(str::to_string);
+                }
                 arg if arg.starts_with("--retry=") => {
                     let count_str = arg.strip_prefix("--retry=").unwrap();
                     options.retry_count = count_str
@@ -157,6 +404,23 @@ fn cp_impl(args: &[String]) -> Result<()> {
                     'r' | 'R' => options.recursive = true,
                     'p' => options.preserve = true,
                     'v' => options.verbose = true,
+                    'f' => options.overwrite.clobber = Clobber::Replace,
+                    'i' => options.overwrite.clobber = Clobber::Ask,
+                    'n' => options.overwrite.clobber = Clobber::Keep,
+                    'u' => options.overwrite.update = true,
+                    'b' => options.overwrite.backup = BackupMode::from_env().map_err(cp_err)?,
+                    'S' => {
+                        let value = args
+                            .next()
+                            .ok_or_else(|| anyhow!("cp: option requires an argument -- 'S'"))?;
+                        suffix = Some(value.clone());
+                    }
+                    'j' => {
+                        let value = args
+                            .next()
+                            .ok_or_else(|| anyhow!("cp: option requires an argument -- 'j'"))?;
+                        options.jobs = parse_jobs(value)?;
+                    }
                     'h' => {
                         print_cp_help();
                         return Ok(());
@@ -172,6 +436,15 @@ fn cp_impl(args: &[String]) -> Result<()> {
     if operands.is_empty() {
         return Err(anyhow!("cp: missing file operand"));
     }
+    // A suffix alone asks for simple backups, as with GNU cp
+    if let Some(suffix) = suffix {
+        if options.overwrite.backup == BackupMode::None {
+            options.overwrite.backup = BackupMode::Simple;
+        }
+        options.overwrite.suffix = suffix;
+    } else {
+        options.overwrite.suffix = crate::safety::default_suffix();
+    }
 
     if operands.len() == 1 {
         return Err(anyhow!("cp: missing destination file operand"));
@@ -181,19 +454,28 @@ fn cp_impl(args: &[String]) -> Result<()> {
     let destination = operands.last().cloned().unwrap();
     let sources = operands[..operands.len() - 1].to_vec();
 
-    let dst_path = PathBuf::from(&destination);
+    let dst_path = session.cwd.join(&destination);
 
     // Check if destination should be a directory when copying multiple sources
     if sources.len() > 1 && !dst_path.is_dir() {
         return Err(anyhow!("cp: target '{}' is not a directory", destination));
     }
 
-    // Enable progress bar for large operations
-    options.show_progress = should_show_progress(&sources, &options)?;
+    // Walk the sources, then copy the files found
+    let planned = plan(&sources, &dst_path, &options, session);
+    planned.and(session.copy_queued(&options))
+}
 
-    // Process each source
+/// Walk `sources`, creating the directories and queueing the files to copy
+/// to `dst_path`
+fn plan(
+    sources: &[String],
+    dst_path: &Path,
+    options: &CopyOptions,
+    session: &mut Session<'_, '_>,
+) -> Result<()> {
     for source in sources {
-        let src_path = Path::new(&source);
+        let src_path = &session.cwd.join(source);
 
         if !src_path.exists() {
             return Err(anyhow!(
@@ -209,7 +491,7 @@ fn cp_impl(args: &[String]) -> Result<()> {
                     .ok_or_else(|| anyhow!("cp: invalid source path '{}'", source))?,
             )
         } else {
-            dst_path.clone()
+            dst_path.to_path_buf()
         };
 
         if src_path.is_dir() {
@@ -219,7 +501,7 @@ fn cp_impl(args: &[String]) -> Result<()> {
                     source
                 ));
             }
-            copy_directory_with_progress(src_path, &target_path, &options).with_context(|| {
+            copy_dir_recursively(src_path, &target_path, options, session).with_context(|| {
                 format!(
                     "Failed to copy directory '{}' to '{}'",
                     source,
@@ -227,69 +509,83 @@ fn cp_impl(args: &[String]) -> Result<()> {
                 )
             })?;
         } else {
-            copy_file_with_metadata(src_path, &target_path, &options).with_context(|| {
-                format!(
-                    "Failed to copy file '{}' to '{}'",
-                    source,
-                    target_path.display()
-                )
-            })?;
-        }
-
-        if options.verbose {
-            info!("'{}' -> '{}'", source, target_path.display());
+            copy_file(src_path, &target_path, options, session)?;
         }
     }
 
     Ok(())
 }
 
-/// Determine if progress bar should be shown based on operation size
-fn should_show_progress(sources: &[String], options: &CopyOptions) -> Result<bool> {
-    if !options.recursive {
-        return Ok(false);
-    }
-
-    let mut total_files = 0;
-    for source in sources {
-        let src_path = Path::new(source);
-        if src_path.is_dir() {
-            total_files += count_files_recursively(src_path)?;
-        } else {
-            total_files += 1;
-        }
+/// The value of `-j` or `--jobs`
+fn parse_jobs(value: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(anyhow!("cp: invalid number of jobs '{}'", value)),
     }
-
-    // Show progress bar if copying more than 100 files
-    Ok(total_files > 100)
 }
 
-/// Count files recursively in a directory
-fn count_files_recursively(dir: &Path) -> Result<u64> {
-    let mut count = 0;
-    let entries = fs::read_dir(dir)
-        .with_context(|| format!("Failed to read directory '{}'", dir.display()))?;
-
-    for entry in entries {
-        let entry = entry
-            .with_context(|| format!("Failed to read directory entry in '{}'", dir.display()))?;
-
-        let file_type = entry
-            .file_type()
-            .with_context(|| format!("Failed to get file type for '{}'", entry.path().display()))?;
+/// A message of `safety` as an error of cp
+fn cp_err(message: String) -> anyhow::Error {
+    anyhow!("cp: {message}")
+}
 
-        if file_type.is_dir() {
-            count += count_files_recursively(&entry.path())?;
-        } else if file_type.is_file() {
-            count += 1;
+/// Whether `a` and `b` are the same file, under the same name or through
+/// a hard or symbolic link
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
         }
     }
+}
 
-    Ok(count)
+/// Queue a single file to be copied unless the overwrite options keep `dst`
+fn copy_file(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    session: &mut Session<'_, '_>,
+) -> Result<()> {
+    // Opening the destination for writing would empty the source
+    if same_file(src, dst) {
+        return Err(anyhow!(
+            "cp: '{}' and '{}' are the same file",
+            session.shown(src),
+            session.shown(dst)
+        ));
+    }
+    if !session.may_overwrite(src, dst, options)? {
+        return Ok(());
+    }
+    let size = fs::metadata(src).map(|meta| meta.len()).unwrap_or(0);
+    let shown = (session.shown(src), session.shown(dst));
+    session.queue.push(Queued {
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        size,
+        shown,
+    });
+    Ok(())
 }
 
-/// Copy a single file with metadata preservation if requested
-fn copy_file_with_metadata(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
+/// Copy a single file with metadata preservation if requested, calling
+/// `progress` with the bytes copied as they are
+fn copy_file_with_progress(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    progress: &mut dyn FnMut(u64),
+) -> Result<()> {
     // Create parent directories if they don't exist
     if let Some(parent) = dst.parent() {
         fs::create_dir_all(parent)
@@ -299,19 +595,15 @@ fn copy_file_with_metadata(src: &Path, dst: &Path, options: &CopyOptions) -> Res
     // Perform the copy with retry logic
     let mut last_error = None;
     for attempt in 0..=options.retry_count {
-        match copy_file_with_advanced_features(src, dst, options) {
+        match copy_file_with_advanced_features(src, dst, options, progress) {
             Ok(()) => {
-                if options.verbose {
-                    if attempt > 0 {
-                        println!(
-                            "Successfully copied '{}' -> '{}' (attempt {})",
-                            src.display(),
-                            dst.display(),
-                            attempt + 1
-                        );
-                    } else {
-                        println!("'{}' -> '{}'", src.display(), dst.display());
-                    }
+                if attempt > 0 {
+                    info!(
+                        "Successfully copied '{}' -> '{}' (attempt {})",
+                        src.display(),
+                        dst.display(),
+                        attempt + 1
+                    );
                 }
                 return Ok(());
             }
@@ -335,21 +627,44 @@ fn copy_file_with_metadata(src: &Path, dst: &Path, options: &CopyOptions) -> Res
 }
 
 /// Advanced file copy with Windows-specific features
-fn copy_file_with_advanced_features(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
+fn copy_file_with_advanced_features(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    progress: &mut dyn FnMut(u64),
+) -> Result<()> {
     #[cfg(windows)]
     if options.preserve_acl || options.preserve_ads || options.preserve_compression {
-        return copy_file_windows_advanced(src, dst, options);
+        return copy_file_windows_advanced(src, dst, options, progress);
     }
 
     // Standard copy for non-Windows or basic options
-    copy_file_standard(src, dst, options)
+    copy_file_standard(src, dst, options, progress)
 }
 
 /// Standard file copy implementation
-fn copy_file_standard(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
-    // Basic file copy
-    fs::copy(src, dst)
-        .with_context(|| format!("Failed to copy '{}' to '{}'", src.display(), dst.display()))?;
+fn copy_file_standard(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    // Clone, or have the kernel copy, where the platform can
+    let copied = nxsh_hal::copy::copy_file(src, dst, options.reflink, options.sparse, progress)
+        .map_err(|e| {
+            anyhow!(
+                "Failed to copy '{}' to '{}': {}",
+                src.display(),
+                dst.display(),
+                hal_message(&e)
+            )
+        })?;
+    debug!(
+        "Copied '{}' ({} bytes, {:?})",
+        src.display(),
+        copied.bytes,
+        copied.method
+    );
 
     if options.preserve {
         preserve_metadata_standard(src, dst)?;
@@ -377,9 +692,14 @@ fn preserve_metadata_standard(src: &Path, dst: &Path) -> Result<()> {
 
 /// Windows-specific advanced copy with basic features (placeholder)
 #[cfg(windows)]
-fn copy_file_windows_advanced(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
+fn copy_file_windows_advanced(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    progress: &mut dyn FnMut(u64),
+) -> Result<()> {
     // For now, use standard copy - Windows-specific features can be added later
-    copy_file_standard(src, dst, options)
+    copy_file_standard(src, dst, options, progress)
 }
 
 /// Preserve Windows compression attribute (placeholder)
@@ -445,17 +765,13 @@ fn calculate_file_hash(path: &Path) -> Result<Vec<u8>> {
     Ok(hasher.finalize().to_vec())
 }
 
-/// Copy directory with progress tracking
-fn copy_directory_with_progress(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
-    if options.show_progress {
-        copy_dir_with_progress_bar(src, dst, options)
-    } else {
-        copy_dir_recursively(src, dst, options)
-    }
-}
-
 /// Enhanced recursive directory copy with metadata preservation
-fn copy_dir_recursively(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
+fn copy_dir_recursively(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    session: &mut Session<'_, '_>,
+) -> Result<()> {
     // Create destination directory
     fs::create_dir_all(dst)
         .with_context(|| format!("Failed to create directory '{}'", dst.display()))?;
@@ -486,7 +802,7 @@ fn copy_dir_recursively(src: &Path, dst: &Path, options: &CopyOptions) -> Result
         let dst_path = dst.join(entry.file_name());
 
         if file_type.is_dir() {
-            copy_dir_recursively(&src_path, &dst_path, options).with_context(|| {
+            copy_dir_recursively(&src_path, &dst_path, options, session).with_context(|| {
                 format!(
                     "Failed to copy subdirectory '{}' to '{}'",
                     src_path.display(),
@@ -494,7 +810,7 @@ fn copy_dir_recursively(src: &Path, dst: &Path, options: &CopyOptions) -> Result
                 )
             })?;
         } else if file_type.is_file() {
-            copy_file_with_metadata(&src_path, &dst_path, options).with_context(|| {
+            copy_file(&src_path, &dst_path, options, session).with_context(|| {
                 format!(
                     "Failed to copy file '{}' to '{}'",
                     src_path.display(),
@@ -502,6 +818,9 @@ fn copy_dir_recursively(src: &Path, dst: &Path, options: &CopyOptions) -> Result
                 )
             })?;
         } else if file_type.is_symlink() {
+            if !session.may_overwrite(&src_path, &dst_path, options)? {
+                continue;
+            }
             copy_symlink(&src_path, &dst_path).with_context(|| {
                 format!(
                     "Failed to copy symlink '{}' to '{}'",
@@ -518,96 +837,6 @@ fn copy_dir_recursively(src: &Path, dst: &Path, options: &CopyOptions) -> Result
     Ok(())
 }
 
-/// Copy directory with progress bar
-fn copy_dir_with_progress_bar(src: &Path, dst: &Path, options: &CopyOptions) -> Result<()> {
-    // Count total files first
-    let total_files = count_files_recursively(src)?;
-
-    if total_files == 0 {
-        return copy_dir_recursively(src, dst, options);
-    }
-
-    // Create progress tracker
-    let mut progress = ProgressTracker::new(total_files, true);
-
-    // Copy with progress tracking
-    copy_dir_with_progress_tracking(src, dst, options, &mut progress)?;
-
-    progress.finish();
-    Ok(())
-}
-
-/// Recursive copy with progress tracking
-fn copy_dir_with_progress_tracking(
-    src: &Path,
-    dst: &Path,
-    options: &CopyOptions,
-    progress: &mut ProgressTracker,
-) -> Result<()> {
-    // Create destination directory
-    fs::create_dir_all(dst)
-        .with_context(|| format!("Failed to create directory '{}'", dst.display()))?;
-
-    // Preserve directory metadata if requested
-    if options.preserve {
-        preserve_metadata(src, dst).with_context(|| {
-            format!(
-                "Failed to preserve directory metadata for '{}'",
-                dst.display()
-            )
-        })?;
-    }
-
-    // Read directory entries
-    let entries = fs::read_dir(src)
-        .with_context(|| format!("Failed to read directory '{}'", src.display()))?;
-
-    for entry in entries {
-        let entry = entry
-            .with_context(|| format!("Failed to read directory entry in '{}'", src.display()))?;
-
-        let file_type = entry
-            .file_type()
-            .with_context(|| format!("Failed to get file type for '{}'", entry.path().display()))?;
-
-        let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
-
-        if file_type.is_dir() {
-            copy_dir_with_progress_tracking(&src_path, &dst_path, options, progress).with_context(
-                || {
-                    format!(
-                        "Failed to copy subdirectory '{}' to '{}'",
-                        src_path.display(),
-                        dst_path.display()
-                    )
-                },
-            )?;
-        } else if file_type.is_file() {
-            copy_file_with_metadata(&src_path, &dst_path, options).with_context(|| {
-                format!(
-                    "Failed to copy file '{}' to '{}'",
-                    src_path.display(),
-                    dst_path.display()
-                )
-            })?;
-            progress.increment();
-        } else if file_type.is_symlink() {
-            copy_symlink(&src_path, &dst_path).with_context(|| {
-                format!(
-                    "Failed to copy symlink '{}' to '{}'",
-                    src_path.display(),
-                    dst_path.display()
-                )
-            })?;
-        } else {
-            eprintln!("Warning: Skipping special file: {}", src_path.display());
-        }
-    }
-
-    Ok(())
-}
-
 /// Copy a symbolic link
 fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
     let target = fs::read_link(src)
@@ -783,20 +1012,10 @@ pub fn execute(
     args: &[String],
     _context: &crate::common::BuiltinContext,
 ) -> crate::common::BuiltinResult<i32> {
-    // Fallback to blocking synchronous cp implementation
-    use std::process::Command;
-    let mut cmd = Command::new("cp");
-    cmd.args(args);
-    match cmd.status() {
-        Ok(status) => {
-            if status.success() {
-                Ok(0)
-            } else {
-                Ok(status.code().unwrap_or(1))
-            }
-        }
+    match cp_on_stdio(args) {
+        Ok(()) => Ok(0),
         Err(e) => {
-            eprintln!("cp: {e}");
+            eprint!("{}", error_line(&e));
             Ok(1)
         }
     }
@@ -1100,6 +1319,103 @@ mod tests {
         assert!(copied_link.is_symlink());
     }
 
+    /// Run cp in `cwd` with `answers` as its input; what it printed and
+    /// the questions it asked
+    fn run_in(cwd: &Path, args: &[&str], answers: &str) -> (Result<()>, String, String) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let mut input = answers.as_bytes();
+        let mut questions = Vec::new();
+        let mut prompter = Prompter::new("cp", None, false, &mut input, &mut questions);
+        let mut session = Session::new(&mut prompter, cwd.to_path_buf());
+        let result = cp_impl(&args, &mut session);
+        let stdout = session.stdout;
+        (result, stdout, String::from_utf8(questions).unwrap())
+    }
+
+    #[test]
+    fn keeps_asks_about_or_backs_up_existing_files() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a"), "new").unwrap();
+        fs::write(dir.path().join("b"), "old").unwrap();
+        let content = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+
+        let (result, stdout, _) = run_in(dir.path(), &["-nv", "a", "b"], "");
+        assert!(result.is_ok());
+        assert_eq!((stdout.as_str(), content("b").as_str()), ("", "old"));
+
+        let (result, _, questions) = run_in(dir.path(), &["-i", "a", "b"], "no\n");
+        assert!(result.is_ok());
+        assert_eq!(questions, "cp: overwrite 'b'? ");
+        assert_eq!(content("b"), "old");
+
+        let (result, stdout, _) = run_in(dir.path(), &["-v", "-S", ".bak", "a", "b"], "");
+        assert!(result.is_ok());
+        assert_eq!(stdout, "'a' -> 'b'\n");
+        assert_eq!(
+            (content("b").as_str(), content("b.bak").as_str()),
+            ("new", "old")
+        );
+
+        // -u leaves b, now as new as a
+        fs::write(dir.path().join("b"), "newer").unwrap();
+        let (result, _, _) = run_in(dir.path(), &["-u", "a", "b"], "");
+        assert!(result.is_ok());
+        assert_eq!(content("b"), "newer");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_to_copy_a_file_onto_itself() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a"), "keep").unwrap();
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        for (args, message) in [
+            (["a", "a"], "cp: 'a' and 'a' are the same file"),
+            (["a", "b"], "cp: 'a' and 'b' are the same file"),
+        ] {
+            let (result, _, _) = run_in(dir.path(), &args, "");
+            assert_eq!(result.unwrap_err().to_string(), message);
+        }
+        assert_eq!(fs::read_to_string(dir.path().join("a")).unwrap(), "keep");
+    }
+
+    #[test]
+    fn copies_trees_several_files_at_a_time() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        for n in 0..12 {
+            let sub = src.join(format!("d{}", n % 3));
+            fs::create_dir_all(&sub).unwrap();
+            fs::write(sub.join(format!("f{n}")), vec![n as u8; 1000 * n]).unwrap();
+        }
+
+        let (result, stdout, _) = run_in(dir.path(), &["-rv", "-j", "4", "src", "dst"], "");
+        result.unwrap();
+        for n in 0..12 {
+            let name = format!("d{}/f{n}", n % 3);
+            assert_eq!(
+                fs::read(dir.path().join("dst").join(&name)).unwrap(),
+                vec![n as u8; 1000 * n]
+            );
+            assert!(stdout.contains(&format!("'src/{name}' -> 'dst/{name}'\n")));
+        }
+
+        let (result, _, _) = run_in(
+            dir.path(),
+            &["--reflink=never", "--sparse=always", "src/d1/f4", "g"],
+            "",
+        );
+        result.unwrap();
+        assert_eq!(fs::read(dir.path().join("g")).unwrap(), vec![4u8; 4000]);
+        for args in [
+            &["-j", "0", "g", "h"][..],
+            &["--reflink=sometimes", "g", "h"],
+        ] {
+            assert!(run_in(dir.path(), args, "").0.is_err());
+        }
+    }
+
     /// Test metadata preservation with new test framework
     #[test]
     fn test_preserve_metadata_new() -> Result<()> {
@@ -1114,7 +1430,7 @@ mod tests {
             ..Default::default()
         };
 
-        copy_file_with_metadata(&src_file, &dst_file, &options)?;
+        copy_file_with_progress(&src_file, &dst_file, &options, &mut |_| {})?;
 
         let src_metadata = fs::metadata(&src_file)?;
         let dst_metadata = fs::metadata(&dst_file)?;
@@ -1139,7 +1455,7 @@ mod tests {
             ..Default::default()
         };
 
-        copy_file_with_metadata(&src_file, &dst_file, &options)?;
+        copy_file_with_progress(&src_file, &dst_file, &options, &mut |_| {})?;
 
         // Verify the copy was successful and content matches
         let dst_content = fs::read_to_string(&dst_file)?;
@@ -1161,7 +1477,7 @@ mod tests {
             ..Default::default()
         };
 
-        copy_file_with_metadata(&src_file, &dst_file, &options)?;
+        copy_file_with_progress(&src_file, &dst_file, &options, &mut |_| {})?;
 
         assert!(dst_file.exists());
         assert_eq!(fs::read_to_string(&dst_file)?, "Test content for retry");
@@ -1198,7 +1514,7 @@ mod tests {
             ..Default::default()
         };
 
-        copy_file_with_metadata(&src_file, &dst_file, &options)?;
+        copy_file_with_progress(&src_file, &dst_file, &options, &mut |_| {})?;
 
         assert!(dst_file.exists());
         assert_eq!(fs::read_to_string(&dst_file)?, "Verbose test content");
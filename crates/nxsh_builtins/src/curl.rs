@@ -1,55 +1,1291 @@
-//! `curl` builtin - HTTP client utility.
-//!
-//! Delegates to the system `curl` binary when available to provide complete
-//! HTTP functionality. When the binary is unavailable, falls back to a simple
-//! internal implementation using ureq.
-
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-
-/// Entry point for the `curl` builtin.
-pub fn curl_cli(args: &[String]) -> Result<()> {
-    // Prefer system curl when available.
-    if let Ok(path) = which("curl") {
-        let status = Command::new(path)
-            .args(args)
-            .status()
-            .map_err(|e| anyhow!("curl: failed to launch backend: {e}"))?;
-        std::process::exit(status.code().unwrap_or(1));
-    }
-
-    #[cfg(feature = "net-http")]
-    {
-        // Lightweight built-in fallback: only supports `curl <URL>` (simple GET).
-        if args.len() == 1 {
-            let url = &args[0];
-            let body = ureq::get(url)
-                .call()
-                .map_err(|e| anyhow!("curl: request failed: {e}"))?
-                .into_string()
-                .map_err(|e| anyhow!("curl: failed to read body: {e}"))?;
-            print!("{body}");
+//! `curl` builtin - transfer data from and to HTTP servers
+//!
+//! Syntax:
+//!   curl [OPTIONS] URL
+//!
+//! Fetches URL, taken as `http://` when it has no scheme, and prints the
+//! body of the reply. Data given with `-d`, `--data-binary`, `--json` or
+//! `-F` is sent with POST, unless `-X` names another method or `-G` moves
+//! the `-d` data into the query string.
+//!
+//! Options:
+//!   -X, --request METHOD      the method to use
+//!   -H, --header 'NAME: VALUE'
+//!                             add a header, replacing a default one of that
+//!                             name; 'NAME:' leaves the default out
+//!   -A, --user-agent TEXT     the User-Agent header
+//!   -d, --data DATA           send DATA, or @FILE or @- for standard input
+//!                             without its line breaks; several are joined
+//!                             with &
+//!       --data-binary DATA    the same, keeping a file's bytes as they are
+//!       --data-raw DATA       DATA as it is, even when it starts with @
+//!       --json DATA           send DATA as JSON, ask for JSON back and print
+//!                             a JSON reply indented
+//!   -F, --form NAME=VALUE     a multipart/form-data field; NAME=@FILE
+//!                             uploads FILE and NAME=<FILE sends its text,
+//!                             either taking ;type=TYPE and ;filename=NAME
+//!   -G, --get                 put the -d data in the query string
+//!   -u, --user USER:PASSWORD  basic authentication
+//!       --oauth2-bearer TOKEN bearer token authentication
+//!   -L, --location            follow redirects
+//!       --max-redirs N        follow at most N redirects, 50 by default and
+//!                             -1 for no limit
+//!   -x, --proxy [http://]HOST[:PORT]
+//!                             the proxy to use, '' for none
+//!       --noproxy LIST        hosts to reach without a proxy
+//!       --retry N             try again up to N times after a transient
+//!                             failure
+//!       --retry-delay SECS    wait SECS between tries
+//!       --retry-max-time SECS stop trying again SECS after the first try
+//!       --retry-connrefused   count a refused connection as transient
+//!   -m, --max-time SECS       give up on a try after SECS
+//!       --connect-timeout SECS
+//!                             give up connecting after SECS
+//!   -i, --include             print the reply's headers before its body
+//!   -I, --head                send HEAD and print only the headers
+//!   -o, --output FILE         write to FILE instead of standard output
+//!   -O, --remote-name         write to the file the URL's path ends in
+//!   -f, --fail                fail with status 22 and no output when the
+//!                             server answers with an error
+//!   -s, --silent              print no errors or warnings
+//!   -S, --show-error          print errors even with -s
+//!   -v, --verbose             show the requests and replies' headers on
+//!                             standard error
+//!   -0, --http1.0             speak HTTP/1.0
+//!       --http1.1             speak HTTP/1.1, which is the default
+//!
+//! `--http2` is refused: only HTTP/1.x is spoken.
+//!
+//! Without `-x` the proxy comes from `http_proxy` or `all_proxy`, unless
+//! `no_proxy` or `--noproxy` lists the host. Credentials in the URL are
+//! sent as basic authentication. Following a redirect to another host drops
+//! the Authorization and Cookie headers; a 303, or a 301 or 302 after a
+//! POST, turns the request into a GET unless `-X` named the method.
+//!
+//! The transient failures `--retry` tries again after are timeouts and the
+//! replies 408, 429, 500, 502, 503 and 504. Between tries curl waits for
+//! `--retry-delay`, or 1 second doubling with each try, or as long as a
+//! Retry-After header asks.
+//!
+//! In a structured pipeline `curl --json` gives the reply as a value, and
+//! plain `curl` the body as text for the conversions:
+//!
+//!   curl --json '{"name":"nxsh"}' $url | get results.0.id
+//!   curl -s $url | from-yaml
+//!
+//! HTTP is spoken by the shell's own HTTP/1.1 client, which has no TLS:
+//! `https` URLs, and redirects to them, are handed to the system's `curl`
+//! when there is one. HTTP/2 is not spoken.
+//!
+//! The exit status is curl's: 0 on success, 2 for bad options, 3 for a
+//! malformed URL, 5, 6 and 7 when the proxy or host cannot be resolved or
+//! reached, 22 for an HTTP error with `-f`, 23 when the output cannot be
+//! written, 26 when a file to send cannot be read, 28 for a timeout and 47
+//! for too many redirects.
+
+use crate::common::http::{self, Proxy, Request, Response, Transport};
+use crate::common::{io_message, seconds, BuiltinContext, BuiltinResult};
+use base64::{engine::general_purpose, Engine as _};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use rand::{rngs::OsRng, RngCore};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Redirects followed without `--max-redirs`
+const DEFAULT_MAX_REDIRS: i64 = 50;
+
+/// The User-Agent header without `-A`
+const USER_AGENT: &str = concat!("curl/", env!("CARGO_PKG_VERSION"), " (nxsh)");
+
+/// The longest a doubling wait between tries grows
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(600);
+
+/// Replies worth trying again
+const TRANSIENT_STATUSES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+/// How often a wait between tries checks the shell's time limit
+const TICK: Duration = Duration::from_millis(50);
+
+/// The options that take a value, by their long names
+const VALUED: &[&str] = &[
+    "--request",
+    "--header",
+    "--user-agent",
+    "--data",
+    "--data-ascii",
+    "--data-binary",
+    "--data-raw",
+    "--json",
+    "--form",
+    "--user",
+    "--oauth2-bearer",
+    "--max-redirs",
+    "--proxy",
+    "--noproxy",
+    "--retry",
+    "--retry-delay",
+    "--retry-max-time",
+    "--max-time",
+    "--connect-timeout",
+    "--output",
+    "--url",
+];
+
+/// The short options and their long names
+const SHORT: &[(char, &str)] = &[
+    ('X', "--request"),
+    ('H', "--header"),
+    ('A', "--user-agent"),
+    ('d', "--data"),
+    ('F', "--form"),
+    ('G', "--get"),
+    ('u', "--user"),
+    ('L', "--location"),
+    ('x', "--proxy"),
+    ('m', "--max-time"),
+    ('i', "--include"),
+    ('I', "--head"),
+    ('o', "--output"),
+    ('O', "--remote-name"),
+    ('f', "--fail"),
+    ('s', "--silent"),
+    ('S', "--show-error"),
+    ('v', "--verbose"),
+    ('0', "--http1.0"),
+];
+
+/// The `curl` builtin command implementation
+pub struct CurlCommand;
+
+impl Builtin for CurlCommand {
+    fn name(&self) -> &'static str {
+        "curl"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Transfer data from and to HTTP servers"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send HTTP requests with custom methods, headers, data, forms and authentication, \
+         following redirects, using proxies and retrying transient failures."
+    }
+
+    fn usage(&self) -> &'static str {
+        "curl [-LisSfvIGO] [-X METHOD] [-H HEADER] [-d DATA] [-F FIELD] [--json DATA] \
+         [-u USER:PASSWORD] [-x PROXY] [--retry N] [-m SECS] [-o FILE] URL"
+    }
+
+    fn help(&self) -> &'static str {
+        "Transfer data over HTTP. Use 'curl -L example.com' to follow redirects, \
+         'curl --json '{\"a\":1}' URL' to post JSON or 'curl -F file=@notes.txt URL' \
+         to upload a file."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run_in(ctx, args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run curl for the legacy dispatcher on the process's standard streams
+/// and environment
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(
+        args,
+        &mut Env {
+            cwd: &cwd,
+            stdin: &mut io::stdin(),
+            input: None,
+            var: &|name| std::env::var(name).ok(),
+            stopped: &|| false,
+        },
+    );
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// The body of the reply for a structured pipeline, and whether `--json`
+/// asked for it to be read as JSON
+pub(crate) fn reply_body(
+    ctx: &mut ShellContext,
+    args: &[String],
+) -> Result<(Vec<u8>, bool), String> {
+    let outcome = run_in(ctx, args);
+    if outcome.status != 0 {
+        let message = String::from_utf8_lossy(&outcome.stderr)
+            .trim_end()
+            .to_string();
+        return Err(if message.is_empty() {
+            format!("curl: exit status {}", outcome.status)
+        } else {
+            message
+        });
+    }
+    let json = Options::parse(args).is_ok_and(|options| options.json());
+    Ok((outcome.stdout, json))
+}
+
+fn run_in(ctx: &mut ShellContext, args: &[String]) -> Outcome {
+    // Standard input moves out for the run, leaving the context free to
+    // look up variables and tell when the shell's time is up
+    let cwd = ctx.cwd.clone();
+    let mut stdin = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+    let outcome = run(
+        args,
+        &mut Env {
+            cwd: &cwd,
+            stdin: &mut *stdin,
+            input: None,
+            var: &|name| ctx.get_var(name),
+            stopped: &|| ctx.is_timed_out(),
+        },
+    );
+    ctx.stdin = stdin;
+    outcome
+}
+
+/// What a run reads besides its options
+struct Env<'a> {
+    cwd: &'a Path,
+    stdin: &'a mut dyn Read,
+    /// Standard input once read, for `@-`
+    input: Option<Vec<u8>>,
+    var: &'a dyn Fn(&str) -> Option<String>,
+    stopped: &'a dyn Fn() -> bool,
+}
+
+impl Env<'_> {
+    /// The bytes of FILE, or of standard input for `-`
+    fn read(&mut self, path: &str) -> Result<Vec<u8>, Failure> {
+        let unreadable = |_| {
+            Failure::Exit(
+                26,
+                "Failed to open/read local data from file/application".into(),
+            )
+        };
+        if path != "-" {
+            return std::fs::read(self.cwd.join(path)).map_err(unreadable);
+        }
+        if self.input.is_none() {
+            let mut input = Vec::new();
+            self.stdin.read_to_end(&mut input).map_err(unreadable)?;
+            self.input = Some(input);
+        }
+        Ok(self.input.clone().unwrap_or_default())
+    }
+}
+
+#[derive(Default)]
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Why a transfer stopped short
+#[derive(Debug)]
+enum Failure {
+    /// An `https` URL, for the system's curl
+    Https,
+    /// curl's exit status and message
+    Exit(i32, String),
+}
+
+impl From<http::Error> for Failure {
+    fn from(e: http::Error) -> Self {
+        Failure::Exit(e.code(), e.to_string())
+    }
+}
+
+fn run(args: &[String], env: &mut Env) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome {
+                stderr: format!("curl: {message}\n").into_bytes(),
+                status: 2,
+                ..Outcome::default()
+            }
+        }
+    };
+    let mut outcome = Outcome::default();
+    let result = match transfer(&options, env, &mut outcome) {
+        Err(Failure::Https) => external(args, &options, env, &mut outcome),
+        result => result,
+    };
+    if let Err(Failure::Exit(status, message)) = result {
+        outcome.status = status;
+        if !options.silent || options.show_error {
+            outcome
+                .stderr
+                .extend_from_slice(format!("curl: ({status}) {message}\n").as_bytes());
+        }
+    }
+    outcome
+}
+
+/// How each `-d` kind of option reads its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// `-d`: @FILE without its line breaks
+    Ascii,
+    /// `--data-binary`: @FILE as it is
+    Binary,
+    /// `--data-raw`: no @FILE
+    Raw,
+    /// `--json`: as `--data-binary`, pieces joined without &
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Data {
+    encoding: Encoding,
+    value: String,
+}
+
+/// Where a form field's content comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Text(String),
+    /// `@FILE`, uploaded as a file
+    File(String),
+    /// `<FILE`, sent as the field's text
+    FileText(String),
+}
+
+/// A `-F` field
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Part {
+    name: String,
+    source: Source,
+    content_type: Option<String>,
+    filename: Option<String>,
+}
+
+impl Part {
+    fn parse(spec: &str) -> Result<Part, String> {
+        let (name, value) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("option -F: is badly used here: '{spec}'"))?;
+        // `;type=` and `;filename=` follow the value; any other `;` is part
+        // of what comes before it
+        let mut pieces = value.split(';');
+        let mut main = pieces.next().unwrap_or_default().to_string();
+        let mut content_type: Option<String> = None;
+        let mut filename: Option<String> = None;
+        for piece in pieces {
+            if let Some(kind) = piece.strip_prefix("type=") {
+                content_type = Some(kind.to_string());
+            } else if let Some(file) = piece.strip_prefix("filename=") {
+                filename = Some(file.trim_matches('"').to_string());
+            } else {
+                let last = filename
+                    .as_mut()
+                    .or(content_type.as_mut())
+                    .unwrap_or(&mut main);
+                last.push(';');
+                last.push_str(piece);
+            }
+        }
+        let source = if let Some(path) = main.strip_prefix('@') {
+            Source::File(path.to_string())
+        } else if let Some(path) = main.strip_prefix('<') {
+            Source::FileText(path.to_string())
+        } else {
+            Source::Text(main)
+        };
+        Ok(Part {
+            name: name.to_string(),
+            source,
+            content_type,
+            filename,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    url: String,
+    method: Option<String>,
+    /// `-H` values as given
+    headers: Vec<String>,
+    user_agent: Option<String>,
+    data: Vec<Data>,
+    form: Vec<Part>,
+    get: bool,
+    user: Option<String>,
+    bearer: Option<String>,
+    location: bool,
+    /// Redirects to follow, or -1 for any number
+    max_redirs: i64,
+    proxy: Option<String>,
+    noproxy: Option<String>,
+    retry: u32,
+    retry_delay: Option<Duration>,
+    retry_max_time: Option<Duration>,
+    retry_connrefused: bool,
+    max_time: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    include: bool,
+    head: bool,
+    output: Option<String>,
+    remote_name: bool,
+    fail: bool,
+    silent: bool,
+    show_error: bool,
+    verbose: bool,
+    http10: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            url: String::new(),
+            method: None,
+            headers: Vec::new(),
+            user_agent: None,
+            data: Vec::new(),
+            form: Vec::new(),
+            get: false,
+            user: None,
+            bearer: None,
+            location: false,
+            max_redirs: DEFAULT_MAX_REDIRS,
+            proxy: None,
+            noproxy: None,
+            retry: 0,
+            retry_delay: None,
+            retry_max_time: None,
+            retry_connrefused: false,
+            max_time: None,
+            connect_timeout: None,
+            include: false,
+            head: false,
+            output: None,
+            remote_name: false,
+            fail: false,
+            silent: false,
+            show_error: false,
+            verbose: false,
+            http10: false,
+        };
+        let mut urls = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg.starts_with("--") {
+                let value = if VALUED.contains(&arg.as_str()) {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| format!("option {arg}: requires parameter"))?;
+                    Some(value.as_str())
+                } else {
+                    None
+                };
+                options.set(arg, arg, value, &mut urls)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                for (at, flag) in arg[1..].char_indices() {
+                    let shown = format!("-{flag}");
+                    let long = SHORT
+                        .iter()
+                        .find(|(short, _)| *short == flag)
+                        .map(|(_, long)| *long)
+                        .ok_or_else(|| format!("option {shown}: is unknown"))?;
+                    if !VALUED.contains(&long) {
+                        options.set(&shown, long, None, &mut urls)?;
+                        continue;
+                    }
+                    let attached = &arg[2 + at..];
+                    let value = match attached {
+                        "" => iter
+                            .next()
+                            .map(String::as_str)
+                            .ok_or_else(|| format!("option {shown}: requires parameter"))?,
+                        _ => attached,
+                    };
+                    options.set(&shown, long, Some(value), &mut urls)?;
+                    break;
+                }
+            } else {
+                urls.push(arg.clone());
+            }
+        }
+
+        options.url = match urls.as_slice() {
+            [url] => url.clone(),
+            [] => return Err("no URL specified".to_string()),
+            [_, extra, ..] => return Err(format!("only one URL is taken, not '{extra}' too")),
+        };
+        let sends_data = !options.data.is_empty();
+        if (sends_data && !options.form.is_empty())
+            || (options.head && (sends_data || !options.form.is_empty()))
+        {
+            return Err("you can only select one HTTP request method".to_string());
+        }
+        Ok(options)
+    }
+
+    /// Take option `long`, given as `shown`, and its value if it has one
+    fn set(
+        &mut self,
+        shown: &str,
+        long: &str,
+        value: Option<&str>,
+        urls: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let text = value.unwrap_or_default();
+        let data = |encoding| Data {
+            encoding,
+            value: text.to_string(),
+        };
+        let secs = || seconds(text).ok_or_else(|| numeric(shown));
+        match long {
+            "--request" => self.method = Some(text.to_string()),
+            "--header" => self.headers.push(text.to_string()),
+            "--user-agent" => self.user_agent = Some(text.to_string()),
+            "--data" | "--data-ascii" => self.data.push(data(Encoding::Ascii)),
+            "--data-binary" => self.data.push(data(Encoding::Binary)),
+            "--data-raw" => self.data.push(data(Encoding::Raw)),
+            "--json" => self.data.push(data(Encoding::Json)),
+            "--form" => self.form.push(Part::parse(text)?),
+            "--user" => self.user = Some(text.to_string()),
+            "--oauth2-bearer" => self.bearer = Some(text.to_string()),
+            "--max-redirs" => {
+                self.max_redirs = match text.parse::<i64>() {
+                    Ok(count) if count >= -1 => count,
+                    _ => return Err(numeric(shown)),
+                }
+            }
+            "--proxy" => self.proxy = Some(text.to_string()),
+            "--noproxy" => self.noproxy = Some(text.to_string()),
+            "--retry" => self.retry = text.parse().map_err(|_| numeric(shown))?,
+            "--retry-delay" => self.retry_delay = Some(secs()?),
+            "--retry-max-time" => self.retry_max_time = Some(secs()?),
+            "--max-time" => self.max_time = Some(secs()?),
+            "--connect-timeout" => self.connect_timeout = Some(secs()?),
+            "--output" => self.output = Some(text.to_string()),
+            "--url" => urls.push(text.to_string()),
+            "--get" => self.get = true,
+            "--location" => self.location = true,
+            "--retry-connrefused" => self.retry_connrefused = true,
+            "--include" => self.include = true,
+            "--head" => self.head = true,
+            "--remote-name" => self.remote_name = true,
+            "--fail" => self.fail = true,
+            "--silent" => self.silent = true,
+            "--show-error" => self.show_error = true,
+            "--verbose" => self.verbose = true,
+            "--http1.0" => self.http10 = true,
+            "--http1.1" | "--basic" => {}
+            "--http2" => return Err(format!("option {shown}: HTTP/2 is not supported")),
+            _ => return Err(format!("option {shown}: is unknown")),
+        }
+        Ok(())
+    }
+
+    fn json(&self) -> bool {
+        self.data.iter().any(|data| data.encoding == Encoding::Json)
+    }
+
+    /// Whether a `-d` or `-F` value is read from standard input
+    fn reads_stdin(&self) -> bool {
+        self.data
+            .iter()
+            .any(|data| data.encoding != Encoding::Raw && data.value == "@-")
+            || self.form.iter().any(|part| {
+                matches!(&part.source, Source::File(path) | Source::FileText(path) if path == "-")
+            })
+    }
+
+    /// The body the data or form options make, and its Content-Type
+    fn body(&self, env: &mut Env) -> Result<(Vec<u8>, Option<String>), Failure> {
+        if !self.form.is_empty() {
+            return multipart(&self.form, env);
+        }
+        if self.data.is_empty() {
+            return Ok((Vec::new(), None));
+        }
+        let mut body = Vec::new();
+        for data in &self.data {
+            let piece = match (data.encoding, data.value.strip_prefix('@')) {
+                (Encoding::Raw, _) | (_, None) => data.value.as_bytes().to_vec(),
+                (Encoding::Ascii, Some(path)) => {
+                    let mut content = env.read(path)?;
+                    content.retain(|&byte| byte != b'\r' && byte != b'\n');
+                    content
+                }
+                (_, Some(path)) => env.read(path)?,
+            };
+            if !body.is_empty() && data.encoding != Encoding::Json {
+                body.push(b'&');
+            }
+            body.extend_from_slice(&piece);
+        }
+        let content_type = if self.json() {
+            "application/json"
+        } else {
+            "application/x-www-form-urlencoded"
+        };
+        Ok((body, Some(content_type.to_string())))
+    }
+
+    /// The headers of the first request: the defaults, then `-H` replacing
+    /// or dropping them and adding its own
+    fn headers(&self, auth: Option<String>, content_type: Option<String>) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if let Some(auth) = auth {
+            headers.push(("Authorization".to_string(), auth));
+        }
+        match self.user_agent.as_deref() {
+            Some("") => {}
+            agent => headers.push((
+                "User-Agent".to_string(),
+                agent.unwrap_or(USER_AGENT).to_string(),
+            )),
+        }
+        let accept = if self.json() {
+            "application/json"
+        } else {
+            "*/*"
+        };
+        headers.push(("Accept".to_string(), accept.to_string()));
+        if let Some(content_type) = content_type {
+            headers.push(("Content-Type".to_string(), content_type));
+        }
+        for header in &self.headers {
+            // `NAME;` sends the header empty, where `NAME:` drops it
+            let (name, value, empty) = match header.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim(), false),
+                None => match header.strip_suffix(';') {
+                    Some(name) => (name.trim(), "", true),
+                    None => continue,
+                },
+            };
+            headers.retain(|(known, _)| !known.eq_ignore_ascii_case(name));
+            if !value.is_empty() || empty {
+                headers.push((name.to_string(), value.to_string()));
+            }
+        }
+        headers
+    }
+
+    /// The proxy to reach `url` through, if any
+    fn proxy_for(&self, url: &Url, env: &Env) -> Result<Option<Proxy>, Failure> {
+        let unusable = |message| Failure::Exit(5, message);
+        match self.proxy.as_deref() {
+            Some("") => Ok(None),
+            Some(proxy) => {
+                let host = url.host_str().unwrap_or_default();
+                if self
+                    .noproxy
+                    .as_deref()
+                    .is_some_and(|list| http::bypasses(list, host))
+                {
+                    return Ok(None);
+                }
+                Proxy::parse(proxy).map(Some).map_err(unusable)
+            }
+            None => Proxy::from_env(url, self.noproxy.as_deref(), env.var).map_err(unusable),
+        }
+    }
+}
+
+fn numeric(option: &str) -> String {
+    format!("option {option}: expected a proper numerical parameter")
+}
+
+/// URL, taken as `http://` without a scheme
+fn parse_url(text: &str) -> Result<Url, Failure> {
+    let full = if text.contains("://") {
+        text.to_string()
+    } else {
+        format!("http://{text}")
+    };
+    Url::parse(&full)
+        .map_err(|_| Failure::Exit(3, "URL rejected: Malformed input to a URL function".into()))
+}
+
+/// Refuse what the HTTP client cannot speak
+fn check_scheme(url: &Url) -> Result<(), Failure> {
+    match url.scheme() {
+        "http" => Ok(()),
+        "https" => Err(Failure::Https),
+        other => Err(Failure::Exit(
+            1,
+            format!("Protocol \"{other}\" not supported"),
+        )),
+    }
+}
+
+/// Make the request with retries and write what it got
+fn transfer(options: &Options, env: &mut Env, outcome: &mut Outcome) -> Result<(), Failure> {
+    let mut url = parse_url(&options.url)?;
+    check_scheme(&url)?;
+    let (mut body, mut content_type) = options.body(env)?;
+    if options.get && !body.is_empty() {
+        let data = String::from_utf8_lossy(&body).into_owned();
+        let query = match url.query() {
+            Some(query) if !query.is_empty() => format!("{query}&{data}"),
+            _ => data,
+        };
+        url.set_query(Some(&query));
+        body.clear();
+        content_type = None;
+    }
+
+    // Credentials in the URL are sent as basic authentication rather than
+    // with the URL
+    let url_user = (!url.username().is_empty()).then(|| {
+        format!(
+            "{}:{}",
+            http::decode(url.username()),
+            http::decode(url.password().unwrap_or(""))
+        )
+    });
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    let auth = match (&options.bearer, options.user.clone().or(url_user)) {
+        (Some(token), _) => Some(format!("Bearer {token}")),
+        (None, Some(user)) => {
+            let user = if user.contains(':') {
+                user
+            } else {
+                format!("{user}:")
+            };
+            Some(format!("Basic {}", general_purpose::STANDARD.encode(user)))
+        }
+        (None, None) => None,
+    };
+
+    let sends = !body.is_empty() || !options.form.is_empty() || !options.data.is_empty();
+    let method = match &options.method {
+        Some(method) => method.clone(),
+        None if options.head => "HEAD".to_string(),
+        None if sends && !options.get => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+    let request = Request {
+        method,
+        url,
+        headers: options.headers(auth, content_type),
+        body,
+        http10: options.http10,
+    };
+
+    let started = Instant::now();
+    let mut tries_left = options.retry;
+    let mut wait = options.retry_delay.unwrap_or(Duration::from_secs(1));
+    let chain = loop {
+        let attempt = follow(options, request.clone(), env, &mut outcome.stderr);
+        let problem = match &attempt {
+            Err(Failure::Exit(28, _)) => Some("timeout"),
+            Err(Failure::Exit(7, _)) if options.retry_connrefused => Some("connection refused"),
+            Ok(chain)
+                if chain
+                    .last()
+                    .is_some_and(|last| TRANSIENT_STATUSES.contains(&last.status)) =>
+            {
+                Some("HTTP error")
+            }
+            _ => None,
+        };
+        let in_time = options
+            .retry_max_time
+            .is_none_or(|max| started.elapsed() < max);
+        let problem = match problem {
+            Some(problem) if tries_left > 0 && in_time => problem,
+            _ => break attempt?,
+        };
+        let pause = match &attempt {
+            Ok(chain) => chain.last().and_then(retry_after).unwrap_or(wait),
+            Err(_) => wait,
+        };
+        if !options.silent {
+            let secs = pause.as_secs();
+            let message = format!(
+                "Warning: Problem : {problem}. Will retry in {secs} second{}. {tries_left} \
+                 retr{} left.\n",
+                if secs == 1 { "" } else { "s" },
+                if tries_left == 1 { "y" } else { "ies" }
+            );
+            outcome.stderr.extend_from_slice(message.as_bytes());
+        }
+        if sleep(pause, env.stopped) {
+            break attempt?;
+        }
+        tries_left -= 1;
+        if options.retry_delay.is_none() {
+            wait = (wait * 2).min(MAX_RETRY_WAIT);
+        }
+    };
+
+    let Some(last) = chain.last() else {
+        return Ok(());
+    };
+    if options.fail && last.status >= 400 {
+        return Err(Failure::Exit(
+            22,
+            format!("The requested URL returned error: {}", last.status),
+        ));
+    }
+    let mut out = Vec::new();
+    if options.include || options.head {
+        for response in &chain {
+            out.extend_from_slice(response.head().as_bytes());
+        }
+    }
+    if !options.head {
+        if options.json() {
+            out.extend(indent_json(&last.body));
+        } else {
+            out.extend_from_slice(&last.body);
+        }
+    }
+    write_output(options, &request.url, env, outcome, out)
+}
+
+/// Send `request` and the ones its redirects lead to, with `-L`, returning
+/// every reply
+fn follow(
+    options: &Options,
+    mut request: Request,
+    env: &Env,
+    log: &mut Vec<u8>,
+) -> Result<Vec<Response>, Failure> {
+    let origin = (
+        request.url.host_str().map(str::to_string),
+        request.url.port_or_known_default(),
+    );
+    let deadline = options.max_time.map(|max| Instant::now() + max);
+    let mut chain: Vec<Response> = Vec::new();
+    loop {
+        let transport = Transport {
+            proxy: options.proxy_for(&request.url, env)?,
+            connect_timeout: options.connect_timeout,
+            deadline,
+            idle_timeout: None,
+        };
+        let response = http::exchange(&request, &transport)?;
+        if options.verbose {
+            trace(log, &request, &transport, &response);
+        }
+        let redirect = options.location && matches!(response.status, 301 | 302 | 303 | 307 | 308);
+        let location = response.header("location").map(str::to_string);
+        let status = response.status;
+        chain.push(response);
+        let (true, Some(location)) = (redirect, location) else {
+            return Ok(chain);
+        };
+        let followed = chain.len() as i64 - 1;
+        if options.max_redirs >= 0 && followed >= options.max_redirs {
+            return Err(Failure::Exit(
+                47,
+                format!("Maximum ({}) redirects followed", options.max_redirs),
+            ));
+        }
+        let next = request.url.join(&location).map_err(|_| {
+            Failure::Exit(3, "URL rejected: Malformed input to a URL function".into())
+        })?;
+        check_scheme(&next)?;
+
+        let to_get = (status == 303 && request.method != "HEAD")
+            || (matches!(status, 301 | 302) && request.method == "POST");
+        if to_get && options.method.is_none() {
+            request.method = "GET".to_string();
+            request.body.clear();
+            request.headers.retain(|(name, _)| {
+                !name.eq_ignore_ascii_case("content-type")
+                    && !name.eq_ignore_ascii_case("content-length")
+            });
+        }
+        let same_origin = next.host_str().map(str::to_string) == origin.0
+            && next.port_or_known_default() == origin.1;
+        if !same_origin {
+            request.headers.retain(|(name, _)| {
+                !name.eq_ignore_ascii_case("authorization") && !name.eq_ignore_ascii_case("cookie")
+            });
+        }
+        request.url = next;
+    }
+}
+
+/// How long a reply's Retry-After header asks to wait, in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    let secs = response.header("retry-after")?.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_WAIT))
+}
+
+/// Wait for `pause`, returning true when the run was stopped meanwhile
+fn sleep(pause: Duration, stopped: &dyn Fn() -> bool) -> bool {
+    let until = Instant::now() + pause;
+    loop {
+        if stopped() {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= until {
+            return false;
+        }
+        thread::sleep(TICK.min(until - now));
+    }
+}
+
+/// `-v`: where curl connected, the request's head and the reply's
+fn trace(log: &mut Vec<u8>, request: &Request, transport: &Transport, response: &Response) {
+    let host = match &transport.proxy {
+        Some(proxy) => proxy.host.clone(),
+        None => request.url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut text = format!(
+        "* Connected to {host} ({}) port {}\n",
+        response.peer.ip(),
+        response.peer.port()
+    );
+    for line in http::request_head(request, transport).lines() {
+        text.push_str(&format!("> {line}\n"));
+    }
+    for line in response.head().lines() {
+        text.push_str(&format!("< {line}\n"));
+    }
+    log.extend_from_slice(text.as_bytes());
+}
+
+/// Write what the transfer got to standard output or the `-o` or `-O` file
+fn write_output(
+    options: &Options,
+    url: &Url,
+    env: &Env,
+    outcome: &mut Outcome,
+    out: Vec<u8>,
+) -> Result<(), Failure> {
+    let path = match (&options.output, options.remote_name) {
+        (Some(path), _) => path.clone(),
+        (None, true) => {
+            let name = url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .unwrap_or_default();
+            if name.is_empty() {
+                return Err(Failure::Exit(23, "Remote file name has no length".into()));
+            }
+            http::decode(name)
+        }
+        (None, false) => {
+            outcome.stdout.extend(out);
             return Ok(());
         }
-        Err(anyhow!(
-            "curl: internal fallback enabled but only supports simple GET (curl <URL>)"
-        ))
+    };
+    if path == "-" {
+        outcome.stdout.extend(out);
+        return Ok(());
+    }
+    std::fs::write(env.cwd.join(&path), out).map_err(|e| {
+        Failure::Exit(
+            23,
+            format!("Failure writing output to {path}: {}", io_message(&e)),
+        )
+    })
+}
+
+/// A multipart/form-data body of the `-F` fields, and its Content-Type
+fn multipart(parts: &[Part], env: &mut Env) -> Result<(Vec<u8>, Option<String>), Failure> {
+    let mut random = [0u8; 8];
+    OsRng.fill_bytes(&mut random);
+    let token: String = random.iter().map(|byte| format!("{byte:02x}")).collect();
+    let boundary = format!("------------------------{token}");
+    let mut body = Vec::new();
+    for part in parts {
+        let (content, filename, content_type) = match &part.source {
+            Source::Text(text) => (
+                text.as_bytes().to_vec(),
+                part.filename.clone(),
+                part.content_type.clone(),
+            ),
+            Source::FileText(path) => (
+                env.read(path)?,
+                part.filename.clone(),
+                part.content_type.clone(),
+            ),
+            Source::File(path) => {
+                let name = Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.clone());
+                let guessed = mime_guess::from_path(path)
+                    .first_or_octet_stream()
+                    .to_string();
+                (
+                    env.read(path)?,
+                    Some(part.filename.clone().unwrap_or(name)),
+                    Some(part.content_type.clone().unwrap_or(guessed)),
+                )
+            }
+        };
+        let mut head = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+            part.name
+        );
+        if let Some(filename) = filename {
+            head.push_str(&format!("; filename=\"{filename}\""));
+        }
+        head.push_str("\r\n");
+        if let Some(content_type) = content_type {
+            head.push_str(&format!("Content-Type: {content_type}\r\n"));
+        }
+        head.push_str("\r\n");
+        body.extend_from_slice(head.as_bytes());
+        body.extend_from_slice(&content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    Ok((
+        body,
+        Some(format!("multipart/form-data; boundary={boundary}")),
+    ))
+}
+
+/// A JSON document indented by two spaces with its keys in their order, or
+/// `body` as it is when it is not one
+fn indent_json(body: &[u8]) -> Vec<u8> {
+    if serde_json::from_slice::<serde::de::IgnoredAny>(body).is_err() {
+        return body.to_vec();
+    }
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        out.push(b'\n');
+        out.extend(std::iter::repeat(b' ').take(depth * 2));
+    };
+    let mut out = Vec::with_capacity(body.len() * 2);
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut bytes = body.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if in_string {
+            out.push(byte);
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            b'"' => {
+                in_string = true;
+                out.push(byte);
+            }
+            b'{' | b'[' => {
+                while bytes.peek().is_some_and(|next| next.is_ascii_whitespace()) {
+                    bytes.next();
+                }
+                let close = if byte == b'{' { b'}' } else { b']' };
+                if bytes.peek() == Some(&close) {
+                    bytes.next();
+                    out.extend([byte, close]);
+                } else {
+                    depth += 1;
+                    out.push(byte);
+                    newline(&mut out, depth);
+                }
+            }
+            b'}' | b']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(byte);
+            }
+            b',' => {
+                out.push(byte);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            _ => out.push(byte),
+        }
     }
+    out.push(b'\n');
+    out
+}
 
-    #[cfg(not(feature = "net-http"))]
-    {
-        Err(anyhow!(
-            "curl: internal HTTP disabled (built without 'net-http' feature); install system curl or rebuild with --features net-http"
-        ))
+/// Hand the whole command to the system's curl, for the `https` URLs the
+/// shell cannot speak
+fn external(
+    args: &[String],
+    options: &Options,
+    env: &mut Env,
+    outcome: &mut Outcome,
+) -> Result<(), Failure> {
+    let unsupported = || {
+        Failure::Exit(
+            1,
+            "Protocol \"https\" not supported: the shell has no TLS and there is no \
+             system curl to hand it to"
+                .into(),
+        )
+    };
+    let path = which::which("curl").map_err(|_| unsupported())?;
+    let input = if options.reads_stdin() {
+        Some(env.read("-")?)
+    } else {
+        None
+    };
+    let mut child = Command::new(path)
+        .args(args)
+        .current_dir(env.cwd)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| unsupported())?;
+    if let (Some(input), Some(mut pipe)) = (input, child.stdin.take()) {
+        thread::spawn(move || pipe.write_all(&input));
     }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Failure::Exit(1, format!("curl: {}", io_message(&e))))?;
+    outcome.stdout = if options.json() && !options.include && options.output.is_none() {
+        indent_json(&output.stdout)
+    } else {
+        output.stdout
+    };
+    outcome.stderr.extend_from_slice(&output.stderr);
+    outcome.status = output.status.code().unwrap_or(1);
+    Ok(())
 }
 
-/// Execute function stub
-pub fn execute(
-    _args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    fn env_body(options: &Options) -> (Vec<u8>, Option<String>) {
+        let cwd = std::env::temp_dir();
+        let mut stdin = io::Cursor::new(b"line one\nline two\n".to_vec());
+        let mut env = Env {
+            cwd: &cwd,
+            stdin: &mut stdin,
+            input: None,
+            var: &|_| None,
+            stopped: &|| false,
+        };
+        options.body(&mut env).unwrap()
+    }
+
+    #[test]
+    fn parses_short_and_long_options() {
+        let parsed = options(&[
+            "-sSLXPUT", "-H", "X-A: 1", "--retry", "3", "-m2.5", "host/x",
+        ])
+        .unwrap();
+        assert!(parsed.silent && parsed.show_error && parsed.location);
+        assert_eq!(parsed.method.as_deref(), Some("PUT"));
+        assert_eq!(parsed.headers, vec!["X-A: 1"]);
+        assert_eq!(parsed.retry, 3);
+        assert_eq!(parsed.max_time, Some(Duration::from_millis(2500)));
+        assert_eq!(parsed.url, "host/x");
+
+        assert_eq!(options(&["-Q", "u"]).unwrap_err(), "option -Q: is unknown");
+        assert_eq!(
+            options(&["--nope", "u"]).unwrap_err(),
+            "option --nope: is unknown"
+        );
+        assert_eq!(
+            options(&["u", "-H"]).unwrap_err(),
+            "option -H: requires parameter"
+        );
+        assert_eq!(
+            options(&["--retry", "x", "u"]).unwrap_err(),
+            "option --retry: expected a proper numerical parameter"
+        );
+        assert_eq!(options(&[]).unwrap_err(), "no URL specified");
+        assert_eq!(
+            options(&["-d", "a", "-F", "b=c", "u"]).unwrap_err(),
+            "you can only select one HTTP request method"
+        );
+    }
+
+    #[test]
+    fn joins_data_and_reads_it_from_standard_input() {
+        let parsed = options(&["-d", "a=1", "-d", "@-", "--data-raw", "@x", "u"]).unwrap();
+        let (body, content_type) = env_body(&parsed);
+        assert_eq!(body, b"a=1&line oneline two&@x");
+        assert_eq!(
+            content_type.as_deref(),
+            Some("application/x-www-form-urlencoded")
+        );
+
+        let parsed = options(&["--json", "{\"a\":", "--json", "1}", "u"]).unwrap();
+        let (body, content_type) = env_body(&parsed);
+        assert_eq!(body, b"{\"a\":1}");
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert!(!parsed.reads_stdin());
+    }
+
+    #[test]
+    fn builds_headers_from_defaults_and_overrides() {
+        let parsed = options(&[
+            "-H",
+            "accept: text/plain",
+            "-H",
+            "User-Agent:",
+            "-H",
+            "X-E;",
+            "u",
+        ])
+        .unwrap();
+        let headers = parsed.headers(Some("Basic x".to_string()), None);
+        let names: Vec<(&str, &str)> = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Authorization", "Basic x"),
+                ("accept", "text/plain"),
+                ("X-E", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_form_fields() {
+        let part = Part::parse("file=@data.csv;type=text/csv;filename=up.csv").unwrap();
+        assert_eq!(part.source, Source::File("data.csv".to_string()));
+        assert_eq!(part.content_type.as_deref(), Some("text/csv"));
+        assert_eq!(part.filename.as_deref(), Some("up.csv"));
+        let part = Part::parse("note=a;b").unwrap();
+        assert_eq!(part.source, Source::Text("a;b".to_string()));
+        assert_eq!(
+            Part::parse("<notes").unwrap_err(),
+            "option -F: is badly used here: '<notes'"
+        );
+    }
+
+    #[test]
+    fn indents_json_keeping_key_order() {
+        assert_eq!(
+            String::from_utf8(indent_json(br#"{"b":[1,{"x":"a,b:{"}],"a":{},"c":[ ]}"#)).unwrap(),
+            "{\n  \"b\": [\n    1,\n    {\n      \"x\": \"a,b:{\"\n    }\n  ],\n  \"a\": {},\n  \"c\": []\n}\n"
+        );
+        assert_eq!(indent_json(b"not json"), b"not json");
+    }
 }
@@ -0,0 +1,679 @@
+//! `dd` builtin - copy and convert data in blocks
+//!
+//! Syntax:
+//!   dd [if=FILE] [of=FILE] [bs=BYTES] [ibs=BYTES] [obs=BYTES] [count=N]
+//!      [skip=N] [seek=N] [conv=CONVS] [status=LEVEL]
+//!
+//! Copies standard input, or `if`, to standard output, or `of`, reading
+//! `ibs` bytes at a time and writing `obs` bytes at a time; `bs` sets both
+//! and writes each block as it was read. `count` copies only N input
+//! blocks, `skip` skips N input blocks and `seek` N output blocks first.
+//! Sizes take the suffixes `c` (1), `w` (2), `b` (512), `kB` (1000), `K`
+//! (1024), `MB`, `M` and so on up to `E`, and may be multiplied with `x`.
+//!
+//! `conv` takes a comma-separated list: `notrunc` keeps the rest of an
+//! existing output file, `fsync` and `fdatasync` flush it to disk at the
+//! end, `sync` pads short input blocks with NULs, `excl` fails if the
+//! output exists and `nocreat` if it does not. `status=none` prints
+//! nothing, `noxfer` leaves out the transfer line and `progress` redraws a
+//! progress bar with the throughput and, when the size is known, the time
+//! left while copying.
+//!
+//! Files are opened as HAL file handles, so offsets past 4 GiB work the
+//! same everywhere.
+//!
+//! The exit status is 0 on success and 1 on any error.
+
+use crate::common::{hal_message, io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{FileHandle, FileSystem, HalOpenOptions};
+use nxsh_ui::ProgressBar;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often `status=progress` redraws
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The `dd` builtin command implementation
+pub struct DdCommand;
+
+impl Builtin for DdCommand {
+    fn name(&self) -> &'static str {
+        "dd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Copy and convert data in blocks"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy a file or standard input in blocks of a chosen size, optionally skipping, \
+         seeking and limiting the blocks copied."
+    }
+
+    fn usage(&self) -> &'static str {
+        "dd [if=FILE] [of=FILE] [bs=BYTES] [count=N] [skip=N] [seek=N] [conv=CONVS] \
+         [status=LEVEL]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy and convert data in blocks. Use 'dd if=disk.img of=copy.img bs=1M \
+         status=progress' to watch a large copy."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin, &mut ctx.stderr);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run dd for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock(), &mut io::stderr());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// How much `dd` reports on standard error
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    None,
+    NoTransfer,
+    Default,
+    Progress,
+}
+
+#[derive(Debug)]
+struct Options {
+    input: Option<String>,
+    output: Option<String>,
+    ibs: usize,
+    obs: usize,
+    /// Whether `bs` was given, so blocks are written as they were read
+    block_size: bool,
+    count: Option<u64>,
+    skip: u64,
+    seek: u64,
+    notrunc: bool,
+    fsync: bool,
+    fdatasync: bool,
+    pad: bool,
+    excl: bool,
+    nocreat: bool,
+    status: Status,
+}
+
+/// A number of bytes or blocks with an optional multiplier suffix, or
+/// several joined by `x`
+fn parse_size(text: &str) -> Option<u64> {
+    text.split('x').try_fold(1u64, |total, part| {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(part.len());
+        if digits == 0 {
+            return None;
+        }
+        let number: u64 = part[..digits].parse().ok()?;
+        let multiplier = match &part[digits..] {
+            "" | "c" => 1,
+            "w" => 2,
+            "b" => 512,
+            suffix => {
+                let mut chars = suffix.chars();
+                let letter = chars.next()?.to_ascii_uppercase();
+                let power = "KMGTPE".find(letter)? as u32 + 1;
+                match chars.as_str() {
+                    "" | "iB" => 1024u64.checked_pow(power)?,
+                    "B" => 1000u64.checked_pow(power)?,
+                    _ => return None,
+                }
+            }
+        };
+        total.checked_mul(number)?.checked_mul(multiplier)
+    })
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            input: None,
+            output: None,
+            ibs: 512,
+            obs: 512,
+            block_size: false,
+            count: None,
+            skip: 0,
+            seek: 0,
+            notrunc: false,
+            fsync: false,
+            fdatasync: false,
+            pad: false,
+            excl: false,
+            nocreat: false,
+            status: Status::Default,
+        };
+        for arg in args {
+            let Some((key, value)) = arg.split_once('=') else {
+                return Err(format!("unrecognized operand '{arg}'"));
+            };
+            let number = || parse_size(value).ok_or_else(|| format!("invalid number: '{value}'"));
+            let block = || match number()? {
+                0 => Err(format!("invalid number: '{value}'")),
+                n => usize::try_from(n).map_err(|_| format!("invalid number: '{value}'")),
+            };
+            match key {
+                "if" => options.input = Some(value.to_string()),
+                "of" => options.output = Some(value.to_string()),
+                "bs" => {
+                    options.ibs = block()?;
+                    options.obs = options.ibs;
+                    options.block_size = true;
+                }
+                "ibs" => options.ibs = block()?,
+                "obs" => options.obs = block()?,
+                "count" => options.count = Some(number()?),
+                "skip" => options.skip = number()?,
+                "seek" => options.seek = number()?,
+                "conv" => {
+                    for conversion in value.split(',') {
+                        match conversion {
+                            "notrunc" => options.notrunc = true,
+                            "fsync" => options.fsync = true,
+                            "fdatasync" => options.fdatasync = true,
+                            "sync" => options.pad = true,
+                            "excl" => options.excl = true,
+                            "nocreat" => options.nocreat = true,
+                            _ => return Err(format!("invalid conversion: '{conversion}'")),
+                        }
+                    }
+                }
+                "status" => {
+                    options.status = match value {
+                        "none" => Status::None,
+                        "noxfer" => Status::NoTransfer,
+                        "progress" => Status::Progress,
+                        _ => return Err(format!("invalid status level: '{value}'")),
+                    }
+                }
+                _ => return Err(format!("unrecognized operand '{arg}'")),
+            }
+        }
+        if options.excl && options.nocreat {
+            return Err("cannot combine excl and nocreat".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// A byte count like `1.5 MB` in powers of BASE, or `None` under BASE
+fn human(bytes: f64, base: f64, units: [&str; 6]) -> Option<String> {
+    let mut value = bytes;
+    let mut unit = None;
+    for name in units {
+        if value < base {
+            break;
+        }
+        value /= base;
+        unit = Some(name);
+    }
+    let unit = unit?;
+    Some(if value < 10.0 {
+        format!("{value:.1} {unit}")
+    } else {
+        format!("{value:.0} {unit}")
+    })
+}
+
+const SI: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+const IEC: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// The transfer line: bytes copied, time taken and throughput
+fn transfer(bytes: u64, elapsed: Duration) -> String {
+    let amount = match (
+        human(bytes as f64, 1000.0, SI),
+        human(bytes as f64, 1024.0, IEC),
+    ) {
+        (Some(si), Some(iec)) => format!("{bytes} bytes ({si}, {iec}) copied"),
+        (Some(si), None) => format!("{bytes} bytes ({si}) copied"),
+        _ if bytes == 1 => "1 byte copied".to_string(),
+        _ => format!("{bytes} bytes copied"),
+    };
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        let rate = bytes as f64 / seconds;
+        human(rate, 1000.0, SI).unwrap_or_else(|| format!("{rate:.0} B"))
+    } else {
+        "Infinity B".to_string()
+    };
+    let seconds = crate::awk::format_number("%g", seconds);
+    format!("{amount}, {seconds} s, {rate}/s")
+}
+
+/// Where blocks are read from
+enum Input<'a> {
+    File(FileHandle),
+    Stdin(&'a mut dyn Read),
+}
+
+impl Read for Input<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            Input::Stdin(stdin) => stdin.read(buf),
+        }
+    }
+}
+
+/// Where blocks are written to
+enum Output {
+    File(FileHandle),
+    Stdout(Vec<u8>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::File(file) => file.write(buf),
+            Output::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            Output::Stdout(_) => Ok(()),
+        }
+    }
+}
+
+/// Full and partial blocks counted for the records lines
+#[derive(Debug, Default)]
+struct Records {
+    full: u64,
+    partial: u64,
+}
+
+impl Records {
+    fn add(&mut self, len: usize, block: usize) {
+        if len == block {
+            self.full += 1;
+        } else {
+            self.partial += 1;
+        }
+    }
+}
+
+/// Read once into BUF, retrying interruptions, the way each input block is
+/// taken as one record
+fn read_block(input: &mut Input, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match input.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            other => return other,
+        }
+    }
+}
+
+/// Live `status=progress` line
+struct Progress<'a> {
+    out: &'a mut dyn Write,
+    bar: Option<ProgressBar>,
+    total: Option<u64>,
+    last: Instant,
+    drawn: bool,
+}
+
+impl Progress<'_> {
+    fn update(&mut self, bytes: u64, started: Instant) {
+        if self.last.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last = Instant::now();
+        let elapsed = started.elapsed();
+        let mut line = transfer(bytes, elapsed);
+        if let (Some(bar), Some(total)) = (self.bar.as_mut(), self.total) {
+            let seconds = elapsed.as_secs_f64();
+            if bytes > 0 && total > bytes {
+                let left = (total - bytes) as f64 * seconds / bytes as f64;
+                line.push_str(&format!(", ETA {}s", left.ceil() as u64));
+            }
+            bar.set_position(bytes);
+            bar.set_message(line);
+            line = bar.render();
+        }
+        // Best effort: a terminal that cannot be written to just goes quiet
+        let _ = write!(self.out, "\r{line}\x1b[K");
+        let _ = self.out.flush();
+        self.drawn = true;
+    }
+
+    fn finish(&mut self) {
+        if self.drawn {
+            let _ = writeln!(self.out);
+        }
+    }
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read, progress: &mut dyn Write) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let fail = |mut outcome: Outcome, message: String| {
+        outcome.stderr.extend(format!("dd: {message}\n").as_bytes());
+        outcome.status = 1;
+        outcome
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return fail(outcome, message),
+    };
+    let fs = match FileSystem::new() {
+        Ok(fs) => fs,
+        Err(e) => return fail(outcome, hal_message(&e)),
+    };
+
+    // Open the input and skip its first blocks
+    let skip_bytes = options.skip.saturating_mul(options.ibs as u64);
+    let mut size = None;
+    let mut input = match &options.input {
+        Some(name) => {
+            let path = cwd.join(name);
+            let mut open = HalOpenOptions::new();
+            open.read(true);
+            let mut file = match fs.open(&path, &open) {
+                Ok(file) => file,
+                Err(e) => {
+                    return fail(
+                        outcome,
+                        format!("failed to open '{name}': {}", hal_message(&e)),
+                    )
+                }
+            };
+            size = file.metadata().ok().filter(|m| m.is_file).map(|m| m.size);
+            if skip_bytes > 0 {
+                if let Err(e) = file.seek(SeekFrom::Start(skip_bytes)) {
+                    return fail(
+                        outcome,
+                        format!("'{name}': cannot skip: {}", io_message(&e)),
+                    );
+                }
+            }
+            Input::File(file)
+        }
+        None => Input::Stdin(stdin),
+    };
+    if options.input.is_none() && skip_bytes > 0 {
+        let skipped = io::copy(&mut (&mut input).take(skip_bytes), &mut io::sink());
+        if let Err(e) = skipped {
+            return fail(
+                outcome,
+                format!("'standard input': cannot skip: {}", io_message(&e)),
+            );
+        }
+    }
+
+    // Open the output, position it and cut it short unless told not to
+    let seek_bytes = options.seek.saturating_mul(options.obs as u64);
+    let mut output = match &options.output {
+        Some(name) => {
+            let path = cwd.join(name);
+            let mut open = HalOpenOptions::new();
+            open.write(true)
+                .create(!options.nocreat)
+                .create_new(options.excl);
+            let mut file = match fs.open(&path, &open) {
+                Ok(file) => file,
+                Err(e) => {
+                    return fail(
+                        outcome,
+                        format!("failed to open '{name}': {}", hal_message(&e)),
+                    )
+                }
+            };
+            if !options.notrunc {
+                if let Err(e) = file.set_len(seek_bytes) {
+                    // Devices cannot be truncated and need not be
+                    if file.metadata().map(|m| m.is_file).unwrap_or(false) {
+                        let message = format!("failed to truncate '{name}': {}", hal_message(&e));
+                        return fail(outcome, message);
+                    }
+                }
+            }
+            if seek_bytes > 0 {
+                if let Err(e) = file.seek(SeekFrom::Start(seek_bytes)) {
+                    return fail(
+                        outcome,
+                        format!("'{name}': cannot seek: {}", io_message(&e)),
+                    );
+                }
+            }
+            Output::File(file)
+        }
+        None if seek_bytes > 0 => {
+            return fail(outcome, "'standard output': cannot seek".to_string());
+        }
+        None => Output::Stdout(Vec::new()),
+    };
+    let output_name = options.output.as_deref().unwrap_or("standard output");
+    let input_name = options.input.as_deref().unwrap_or("standard input");
+
+    let total = match (options.count, size) {
+        (Some(count), size) => {
+            let wanted = count.saturating_mul(options.ibs as u64);
+            Some(size.map_or(wanted, |size| wanted.min(size.saturating_sub(skip_bytes))))
+        }
+        (None, size) => size.map(|size| size.saturating_sub(skip_bytes)),
+    };
+    let mut progress = (options.status == Status::Progress).then(|| Progress {
+        out: progress,
+        bar: total.map(ProgressBar::new),
+        total,
+        last: Instant::now(),
+        drawn: false,
+    });
+
+    let started = Instant::now();
+    let mut records_in = Records::default();
+    let mut records_out = Records::default();
+    let mut written = 0u64;
+    let mut block = vec![0u8; options.ibs];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut error = None;
+    loop {
+        if let Some(count) = options.count {
+            if records_in.full + records_in.partial >= count {
+                break;
+            }
+        }
+        let len = match read_block(&mut input, &mut block) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) => {
+                error = Some(format!("error reading '{input_name}': {}", io_message(&e)));
+                break;
+            }
+        };
+        records_in.add(len, options.ibs);
+        if options.pad {
+            block[len..].fill(0);
+        }
+        let data = if options.pad {
+            &block[..]
+        } else {
+            &block[..len]
+        };
+        let result = if options.block_size {
+            output.write_all(data).map(|()| {
+                records_out.add(data.len(), options.obs);
+                written += data.len() as u64;
+            })
+        } else {
+            pending.extend_from_slice(data);
+            let mut result = Ok(());
+            let mut start = 0;
+            while pending.len() - start >= options.obs {
+                let chunk = &pending[start..start + options.obs];
+                result = output.write_all(chunk);
+                if result.is_err() {
+                    break;
+                }
+                records_out.full += 1;
+                written += options.obs as u64;
+                start += options.obs;
+            }
+            pending.drain(..start);
+            result
+        };
+        if let Err(e) = result {
+            error = Some(format!("error writing '{output_name}': {}", io_message(&e)));
+            break;
+        }
+        if let Some(progress) = progress.as_mut() {
+            progress.update(written, started);
+        }
+    }
+    if error.is_none() && !pending.is_empty() {
+        match output.write_all(&pending) {
+            Ok(()) => {
+                records_out.partial += 1;
+                written += pending.len() as u64;
+            }
+            Err(e) => error = Some(format!("error writing '{output_name}': {}", io_message(&e))),
+        }
+    }
+    if error.is_none() {
+        let synced = match &mut output {
+            Output::File(file) if options.fsync => file.sync_all(),
+            Output::File(file) if options.fdatasync => file.sync_data(),
+            _ => Ok(()),
+        };
+        if let Err(e) = synced {
+            error = Some(format!(
+                "fsync failed for '{output_name}': {}",
+                hal_message(&e)
+            ));
+        }
+    }
+    if let Some(progress) = progress.as_mut() {
+        progress.finish();
+    }
+
+    if let Output::Stdout(data) = output {
+        outcome.stdout = data;
+    }
+    if let Some(message) = error {
+        outcome.stderr.extend(format!("dd: {message}\n").as_bytes());
+        outcome.status = 1;
+    }
+    if options.status != Status::None {
+        let mut report = format!(
+            "{}+{} records in\n{}+{} records out\n",
+            records_in.full, records_in.partial, records_out.full, records_out.partial
+        );
+        if options.status != Status::NoTransfer {
+            report.push_str(&transfer(written, started.elapsed()));
+            report.push('\n');
+        }
+        outcome.stderr.extend(report.as_bytes());
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dd(args: &[&str], dir: &Path, input: &[u8]) -> (Vec<u8>, String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut &input[..], &mut io::sink());
+        (
+            outcome.stdout,
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("2b"), Some(1024));
+        assert_eq!(parse_size("1K"), Some(1024));
+        assert_eq!(parse_size("1kB"), Some(1000));
+        assert_eq!(parse_size("3MiB"), Some(3 << 20));
+        assert_eq!(parse_size("2x4w"), Some(16));
+        assert_eq!(parse_size("1Q"), None);
+        assert_eq!(parse_size("x"), None);
+        assert_eq!(parse_size("99999999999E"), None);
+    }
+
+    #[test]
+    fn copies_blocks_from_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (out, err, status) = dd(&["bs=4", "skip=1", "count=2"], dir.path(), b"0123456789ab");
+        assert_eq!(status, 0, "{err}");
+        assert_eq!(out, b"456789ab");
+        assert!(err.starts_with("2+0 records in\n2+0 records out\n8 bytes copied, "));
+
+        let (out, err, _) = dd(&["ibs=3", "obs=2", "status=noxfer"], dir.path(), b"abcdefg");
+        assert_eq!(out, b"abcdefg");
+        assert_eq!(err, "2+1 records in\n3+1 records out\n");
+
+        let (out, _, _) = dd(&["bs=4", "conv=sync", "status=none"], dir.path(), b"abcde");
+        assert_eq!(out, b"abcde\0\0\0");
+    }
+
+    #[test]
+    fn writes_files_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("in"), b"ABCDEFGH").unwrap();
+        std::fs::write(root.join("out"), b"12345678").unwrap();
+
+        let args = [
+            "if=in",
+            "of=out",
+            "bs=2",
+            "skip=1",
+            "seek=1",
+            "count=2",
+            "conv=notrunc",
+        ];
+        assert_eq!(dd(&args, root, b"").2, 0);
+        assert_eq!(std::fs::read(root.join("out")).unwrap(), b"12CDEF78");
+
+        assert_eq!(
+            dd(&["if=in", "of=out", "bs=2", "seek=1", "count=1"], root, b"").2,
+            0
+        );
+        assert_eq!(std::fs::read(root.join("out")).unwrap(), b"12AB");
+
+        let (_, err, status) = dd(&["if=in", "of=out", "conv=excl"], root, b"");
+        assert_eq!(status, 1);
+        assert!(err.starts_with("dd: failed to open 'out': "));
+        assert_eq!(dd(&["bs=0"], root, b"").1, "dd: invalid number: '0'\n");
+    }
+
+    #[test]
+    fn reports_transfers() {
+        assert!(transfer(1, Duration::from_secs(1)).starts_with("1 byte copied, 1 s, "));
+        assert!(transfer(1_048_576, Duration::from_secs(2))
+            .starts_with("1048576 bytes (1.0 MB, 1.0 MiB) copied, 2 s, 524 kB/s"));
+    }
+}
@@ -1,347 +1,482 @@
-use std::collections::HashMap;
-use nxsh_core::{ShellError, ErrorKind}; use nxsh_core::error::RuntimeErrorKind;
-
-pub fn declare_cli(args: &[String]) -> Result<(), ShellError> {
-    if args.is_empty() || args.contains(&"--help".to_string()) {
-        print_help();
-        return Ok(());
-    }
-
-    let mut options = DeclareOptions::default();
-    let mut variables = Vec::new();
-    
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-a" => options.array = true,
-            "-A" => options.associative = true,
-            "-f" => options.function = true,
-            "-F" => options.function_names_only = true,
-            "-g" => options.global = true,
-            "-i" => options.integer = true,
-            "-l" => options.lowercase = true,
-            "-n" => options.nameref = true,
-            "-r" => options.readonly = true,
-            "-t" => options.trace = true,
-            "-u" => options.uppercase = true,
-            "-x" => options.export = true,
-            "-p" => options.print = true,
-            arg if !arg.starts_with('-') => {
-                variables.push(arg.to_string());
-            },
-            _ => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("declare: {}: invalid option", args[i]))),
-        }
-        i += 1;
-    }
-    
-    execute_declare(&options, &variables)
-}
-
-#[derive(Default, Debug)]
-struct DeclareOptions {
-    array: bool,
+//! `declare` builtin - declare variables and arrays and give them attributes
+//!
+//! Syntax: declare [-aAgirx] [-p] [NAME[=VALUE] | NAME=(ELEM ...) | NAME[SUB]=VALUE ...]
+//!
+//! `-a` makes each NAME an indexed array and `-A` an associative array.
+//! Compound values `(a b c)` fill an array in order; `([key]=value ...)`
+//! sets explicit indices or keys. `-i` evaluates the value as an arithmetic
+//! expression, `-x` exports and `-r` marks the variable readonly. With `-p`,
+//! or with no NAMEs, declarations are printed in a form the shell can read
+//! back.
+//!
+//! Inside a function `declare` makes each NAME local to the call, like
+//! `local`; `-g` assigns the variable visible to the caller instead.
+//!
+//! Exit status: 0 on success, 1 if a NAME is invalid or not found, 2 on a
+//! usage error.
+
+use nxsh_core::context::{ShellArray, ShellContext, ShellVariable};
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+
+/// The `declare` builtin command implementation
+pub struct DeclareCommand;
+
+/// Attribute flags given on the command line
+#[derive(Debug, Default)]
+struct DeclareFlags {
+    indexed: bool,
     associative: bool,
-    function: bool,
-    function_names_only: bool,
-    global: bool,
     integer: bool,
-    lowercase: bool,
-    nameref: bool,
     readonly: bool,
-    trace: bool,
-    uppercase: bool,
-    export: bool,
+    export: Option<bool>,
     print: bool,
+    global: bool,
 }
 
-fn print_help() {
-    println!("declare - declare variables and give them attributes
-
-USAGE:
-    declare [-aAfFgilnrtux] [-p] [name[=value] ...]
-
-DESCRIPTION:
-    Declare variables and give them attributes. If no NAMEs are given,
-    display the attributes and values of all variables.
-
-OPTIONS:
-    -a      Each NAME is an indexed array variable
-    -A      Each NAME is an associative array variable  
-    -f      Use function names only
-    -F      Display function names without definitions
-    -g      Create global variables when used in a shell function
-    -i      The variable is to be treated as an integer
-    -l      Convert NAMEs to lower case on assignment
-    -n      Each NAME is a nameref and will be assigned to by reference
-    -p      Display the attributes and value of each NAME
-    -r      Make NAMEs readonly
-    -t      Give each NAME the trace attribute
-    -u      Convert NAMEs to upper case on assignment
-    -x      Mark NAMEs for export to subsequent commands
+impl Builtin for DeclareCommand {
+    fn name(&self) -> &'static str {
+        "declare"
+    }
 
-EXAMPLES:
-    # Declare variables with attributes
-    declare -i count=0
-    declare -r readonly_var=\"constant\"
-    declare -x export_var=\"exported\"
-    
-    # Declare arrays
-    declare -a indexed_array
-    declare -A associative_array
-    
-    # Display all variables
-    declare -p
-    
-    # Display specific variable
-    declare -p my_var
-    
-    # Function-related
-    declare -f              # Show all function definitions
-    declare -F              # Show all function names only
+    fn synopsis(&self) -> &'static str {
+        "Declare variables and give them attributes"
+    }
 
-EXIT STATUS:
-    0   Success
-    1   Invalid option or assignment error");
-}
+    fn description(&self) -> &'static str {
+        "Declare shell variables, indexed arrays (-a) and associative arrays (-A), \
+         optionally assigning values and setting the integer, readonly and export attributes."
+    }
 
-fn execute_declare(options: &DeclareOptions, variables: &[String]) -> Result<(), ShellError> {
-    if options.print {
-        return print_declarations(variables);
+    fn usage(&self) -> &'static str {
+        "declare [-aAgirx] [-p] [name[=value] ...]"
     }
-    
-    if options.function || options.function_names_only {
-        return handle_function_declarations(options);
+
+    fn help(&self) -> &'static str {
+        "Declare variables and arrays. Use -p to print declarations."
     }
-    
-    if variables.is_empty() {
-        return print_all_declarations();
+
+    fn affects_shell_state(&self) -> bool {
+        true // declare creates and modifies variables
     }
-    
-    for var_spec in variables {
-        process_variable_declaration(var_spec, options)?;
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        Ok(run(ctx, args))
     }
-    
-    Ok(())
 }
 
-fn print_declarations(variables: &[String]) -> Result<(), ShellError> {
-    if variables.is_empty() {
-        return print_all_declarations();
-    }
-    
-    for var_name in variables {
-        if let Some(var_info) = get_variable_info(var_name) {
-            println!("{}", format_variable_declaration(&var_info));
-        } else {
-            eprintln!("declare: {var_name}: not found");
-        }
+impl DeclareCommand {
+    /// Create a new declare command instance
+    pub fn new() -> Self {
+        DeclareCommand
     }
-    
-    Ok(())
 }
 
-fn print_all_declarations() -> Result<(), ShellError> {
-    let variables = get_all_variables();
-    for var_info in variables {
-        println!("{}", format_variable_declaration(&var_info));
+impl Default for DeclareCommand {
+    fn default() -> Self {
+        Self::new()
     }
-    Ok(())
 }
 
-fn handle_function_declarations(options: &DeclareOptions) -> Result<(), ShellError> {
-    // Get all function names from the function registry
-    let functions = crate::function::list_functions();
-    
-    if options.function_names_only {
-        for func_name in functions {
-            println!("declare -f {func_name}");
-        }
-    } else {
-        for func_name in functions {
-            println!("declare -f {func_name}");
-            println!("{func_name} () {{");
-            println!("    # Function body would be displayed here");
-            println!("}}");
-        }
-    }
-    
-    Ok(())
+/// Run `declare` against `ctx`; shared with the legacy `vars` entry point
+pub fn run(ctx: &ShellContext, args: &[String]) -> ExecutionResult {
+    run_as(ctx, args, "declare", DeclareCommand.usage())
 }
 
-fn process_variable_declaration(var_spec: &str, options: &DeclareOptions) -> Result<(), ShellError> {
-    let (var_name, var_value) = parse_variable_assignment(var_spec)?;
-    
-    let mut var_info = VariableInfo {
-        name: var_name.clone(),
-        value: var_value.unwrap_or_default(),
-        attributes: VariableAttributes::default(),
+/// Run `declare` or `local`, which differ only in how they report errors and
+/// in what they list without operands
+pub(crate) fn run_as(
+    ctx: &ShellContext,
+    args: &[String],
+    command: &str,
+    usage: &str,
+) -> ExecutionResult {
+    let (flags, operands) = match parse_flags(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return ExecutionResult::failure(2).with_error(
+                format!("{command}: {message}\n{command}: usage: {usage}\n").into_bytes(),
+            )
+        }
     };
-    
-    // Apply attributes
-    var_info.attributes.array = options.array;
-    var_info.attributes.associative = options.associative;
-    var_info.attributes.integer = options.integer;
-    var_info.attributes.lowercase = options.lowercase;
-    var_info.attributes.readonly = options.readonly;
-    var_info.attributes.uppercase = options.uppercase;
-    var_info.attributes.export = options.export;
-    
-    // Apply transformations
-    if options.lowercase {
-        var_info.value = var_info.value.to_lowercase();
-    } else if options.uppercase {
-        var_info.value = var_info.value.to_uppercase();
+    if flags.indexed && flags.associative {
+        return ExecutionResult::failure(2)
+            .with_error(format!("{command}: cannot use -a and -A together\n").into_bytes());
     }
-    
-    // Validate integer assignment
-    if options.integer && !var_info.value.is_empty()
-        && var_info.value.parse::<i64>().is_err() {
-            return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("declare: {}: invalid integer value", var_info.value)));
+
+    if operands.is_empty() {
+        let listing = if command == "local" {
+            ctx.local_names()
+                .iter()
+                .filter_map(|name| describe(ctx, name))
+                .collect()
+        } else {
+            print_all(ctx, &flags)
+        };
+        return ExecutionResult::success(0).with_output(listing.into_bytes());
+    }
+
+    let mut status = 0;
+    let mut out = String::new();
+    let mut err = String::new();
+    for operand in operands {
+        let result = if flags.print {
+            match describe(ctx, operand) {
+                Some(line) => {
+                    out.push_str(&line);
+                    Ok(())
+                }
+                None => Err(format!("{operand}: not found")),
+            }
+        } else {
+            declare_one(ctx, &flags, operand)
+        };
+        if let Err(message) = result {
+            err.push_str(&format!("{command}: {message}\n"));
+            status = 1;
         }
-    
-    // Store the variable
-    set_variable(var_info)?;
-    
-    Ok(())
+    }
+    ExecutionResult::success(status)
+        .with_output(out.into_bytes())
+        .with_error(err.into_bytes())
 }
 
-fn parse_variable_assignment(spec: &str) -> Result<(String, Option<String>), ShellError> {
-    if let Some(eq_pos) = spec.find('=') {
-        let name = spec[..eq_pos].to_string();
-        let value = spec[eq_pos + 1..].to_string();
-        
-        if !is_valid_variable_name(&name) {
-            return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("declare: '{name}': not a valid identifier")));
+fn parse_flags(args: &[String]) -> Result<(DeclareFlags, &[String]), String> {
+    let mut flags = DeclareFlags::default();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--" {
+            i += 1;
+            break;
         }
-        
-        Ok((name, Some(value)))
-    } else {
-        if !is_valid_variable_name(spec) {
-            return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("declare: '{spec}': not a valid identifier")));
+        let enable = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => break,
+        };
+        if arg.len() == 1 {
+            break;
+        }
+        for letter in arg[1..].chars() {
+            match letter {
+                'a' if enable => flags.indexed = true,
+                'A' if enable => flags.associative = true,
+                'a' | 'A' => {
+                    return Err(format!(
+                        "+{letter}: cannot destroy array variables this way"
+                    ))
+                }
+                'i' => flags.integer = enable,
+                'r' if enable => flags.readonly = true,
+                'r' => return Err("+r: cannot remove the readonly attribute".to_string()),
+                'x' => flags.export = Some(enable),
+                'p' => flags.print = true,
+                'g' => flags.global = true,
+                _ => return Err(format!("{}{letter}: invalid option", &arg[..1])),
+            }
         }
-        
-        Ok((spec.to_string(), None))
+        i += 1;
     }
+    Ok((flags, &args[i..]))
 }
 
-fn is_valid_variable_name(name: &str) -> bool {
-    if name.is_empty() {
-        return false;
+/// Apply one `NAME`, `NAME=VALUE`, `NAME+=VALUE`, `NAME=(...)` or
+/// `NAME[SUB]=VALUE` operand
+fn declare_one(ctx: &ShellContext, flags: &DeclareFlags, operand: &str) -> Result<(), String> {
+    let (target, op, value) = match operand.find('=') {
+        Some(pos) if operand[..pos].ends_with('+') => {
+            (&operand[..pos - 1], "+=", Some(&operand[pos + 1..]))
+        }
+        Some(pos) => (&operand[..pos], "=", Some(&operand[pos + 1..])),
+        None => (operand, "=", None),
+    };
+    let (name, subscript) = match target.find('[') {
+        Some(open) if target.ends_with(']') => {
+            (&target[..open], Some(&target[open + 1..target.len() - 1]))
+        }
+        _ => (target, None),
+    };
+    if !is_identifier(name) {
+        return Err(format!("`{operand}': not a valid identifier"));
     }
-    
-    let first_char = name.chars().next().unwrap();
-    if !first_char.is_ascii_alphabetic() && first_char != '_' {
-        return false;
+    if !flags.global && ctx.in_function() {
+        ctx.make_local(name);
+    }
+    let append = op == "+=";
+
+    // Attributes that change the variable's type come first
+    let existing = ctx.get_array(name);
+    if flags.associative {
+        match &existing {
+            Some(array) if !array.is_associative() => {
+                return Err(format!(
+                    "{name}: cannot convert indexed to associative array"
+                ))
+            }
+            Some(_) => {}
+            None => ctx.set_array(name, ShellArray::new_associative()),
+        }
+    } else if flags.indexed {
+        match &existing {
+            Some(array) if array.is_associative() => {
+                return Err(format!(
+                    "{name}: cannot convert associative to indexed array"
+                ))
+            }
+            Some(_) => {}
+            None => ctx.set_array(name, ShellArray::from_values(ctx.get_var(name))),
+        }
     }
-    
-    name.chars().skip(1).all(|c| c.is_ascii_alphanumeric() || c == '_')
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct VariableInfo {
-    pub name: String,
-    pub value: String,
-    pub attributes: VariableAttributes,
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct VariableAttributes {
-    pub array: bool,
-    pub associative: bool,
-    pub integer: bool,
-    pub lowercase: bool,
-    pub readonly: bool,
-    pub uppercase: bool,
-    pub export: bool,
-}
 
-impl VariableAttributes {
-    fn to_flags(&self) -> String {
-        let mut flags = String::new();
-        
-        if self.array { flags.push('a'); }
-        if self.associative { flags.push('A'); }
-        if self.integer { flags.push('i'); }
-        if self.lowercase { flags.push('l'); }
-        if self.readonly { flags.push('r'); }
-        if self.uppercase { flags.push('u'); }
-        if self.export { flags.push('x'); }
-        
-        flags
+    if let Some(value) = value {
+        match (
+            subscript,
+            value.strip_prefix('(').and_then(|v| v.strip_suffix(')')),
+        ) {
+            (None, Some(elements)) => assign_compound(ctx, name, elements, append)?,
+            (subscript, _) => {
+                let is_element = subscript.is_some() || ctx.get_array(name).is_some();
+                let subscript = subscript.unwrap_or("0");
+                let current = || {
+                    if is_element {
+                        ctx.get_array_element(name, subscript)
+                    } else {
+                        ctx.get_var(name)
+                    }
+                };
+                let value = if flags.integer {
+                    let expr = if append {
+                        format!("{}+({value})", current().unwrap_or_else(|| "0".into()))
+                    } else {
+                        value.to_string()
+                    };
+                    nxsh_core::arithmetic::evaluate(&expr, ctx)
+                        .map_err(|e| format!("{value}: {e}"))?
+                        .to_string()
+                } else if append {
+                    current().unwrap_or_default() + value
+                } else {
+                    value.to_string()
+                };
+                if is_element {
+                    ctx.set_array_element(name, subscript, value)
+                        .map_err(|e| e.to_string())?;
+                } else {
+                    ctx.set_var(name, value);
+                }
+            }
+        }
+    } else if ctx.get_array(name).is_none() && ctx.get_var(name).is_none() {
+        // `declare NAME` creates the variable without a value
+        ctx.set_var(name, "");
     }
-}
 
-fn format_variable_declaration(var_info: &VariableInfo) -> String {
-    let flags = var_info.attributes.to_flags();
-    if flags.is_empty() {
-        format!("declare -- {}=\"{}\"", var_info.name, var_info.value)
-    } else {
-        format!("declare -{} {}=\"{}\"", flags, var_info.name, var_info.value)
+    if flags.readonly || flags.export.is_some() {
+        if ctx.get_array(name).is_some() {
+            // Arrays carry no attributes yet; bash does not export them either
+            return Ok(());
+        }
+        let mut var = ctx
+            .vars
+            .read()
+            .ok()
+            .and_then(|vars| vars.get(name).cloned())
+            .unwrap_or_else(|| ShellVariable::new(ctx.get_var(name).unwrap_or_default()));
+        var.readonly |= flags.readonly;
+        if let Some(export) = flags.export {
+            var.exported = export;
+            if !export {
+                if let Ok(mut env) = ctx.env.write() {
+                    env.remove(name);
+                }
+            }
+        }
+        ctx.set_shell_var(name, var);
     }
+    Ok(())
 }
 
-// Global variable storage (simplified)
-use std::sync::Mutex;
-use once_cell::sync::Lazy;
-
-static VARIABLE_REGISTRY: Lazy<Mutex<HashMap<String, VariableInfo>>> = 
-    Lazy::new(|| Mutex::new(HashMap::new()));
-
-fn set_variable(var_info: VariableInfo) -> Result<(), ShellError> {
-    let mut registry = VARIABLE_REGISTRY.lock().unwrap();
-    
-    // Check if variable is readonly
-    if let Some(existing) = registry.get(&var_info.name) {
-        if existing.attributes.readonly {
-            return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("declare: {}: readonly variable", var_info.name)));
+/// `NAME=(elem ...)`: `[key]=value` elements set explicit indices or keys,
+/// the rest are appended in order
+fn assign_compound(
+    ctx: &ShellContext,
+    name: &str,
+    elements: &str,
+    append: bool,
+) -> Result<(), String> {
+    let base = match ctx.get_array(name) {
+        Some(array) if append => array,
+        Some(array) if array.is_associative() => ShellArray::new_associative(),
+        _ if append => ShellArray::from_values(ctx.get_var(name)),
+        _ => ShellArray::from_values(None),
+    };
+    let associative = base.is_associative();
+    ctx.set_array(name, base);
+    for word in split_words(elements) {
+        let keyed = word.strip_prefix('[').and_then(|w| w.split_once("]="));
+        match keyed {
+            Some((key, value)) => ctx
+                .set_array_element(name, key, unquote(value))
+                .map_err(|e| e.to_string())?,
+            None if associative => {
+                return Err(format!(
+                    "{name}: {}: must use subscript when assigning associative array",
+                    unquote(&word)
+                ))
+            }
+            None => ctx.push_array_element(name, unquote(&word)),
         }
     }
-    
-    registry.insert(var_info.name.clone(), var_info);
     Ok(())
 }
 
-fn get_variable_info(name: &str) -> Option<VariableInfo> {
-    let registry = VARIABLE_REGISTRY.lock().unwrap();
-    registry.get(name).cloned()
+/// Split a compound value on unquoted whitespace, keeping quotes in place
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), c) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (q, '\\') if q != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
 }
 
-fn get_all_variables() -> Vec<VariableInfo> {
-    let registry = VARIABLE_REGISTRY.lock().unwrap();
-    registry.values().cloned().collect()
+/// Remove one level of shell quoting
+fn unquote(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut quote = None;
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (q, '\\') if q != Some('\'') => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
 }
 
-// Public API
-pub fn declare_variable(name: &str, value: &str, attributes: VariableAttributes) -> Result<(), ShellError> {
-    let var_info = VariableInfo {
-        name: name.to_string(),
-        value: value.to_string(),
-        attributes,
-    };
-    
-    set_variable(var_info)
+/// Every variable (or only arrays of one kind with -a/-A) as `declare` lines
+fn print_all(ctx: &ShellContext, flags: &DeclareFlags) -> String {
+    let mut names: Vec<String> = Vec::new();
+    if let Ok(arrays) = ctx.arrays.read() {
+        names.extend(
+            arrays
+                .iter()
+                .filter(|(_, a)| {
+                    (!flags.indexed || !a.is_associative())
+                        && (!flags.associative || a.is_associative())
+                })
+                .map(|(k, _)| k.clone()),
+        );
+    }
+    if !flags.indexed && !flags.associative {
+        if let Ok(vars) = ctx.vars.read() {
+            names.extend(vars.keys().cloned());
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+        .iter()
+        .filter_map(|name| describe(ctx, name))
+        .collect()
 }
 
-pub fn get_variable_value(name: &str) -> Option<String> {
-    get_variable_info(name).map(|info| info.value)
+/// `declare -p NAME` line, or `None` when NAME is unset
+fn describe(ctx: &ShellContext, name: &str) -> Option<String> {
+    if let Some(array) = ctx.get_array(name) {
+        let flag = if array.is_associative() { "-A" } else { "-a" };
+        let elements: Vec<String> = array
+            .keys()
+            .into_iter()
+            .zip(array.values())
+            .map(|(k, v)| format!("[{k}]={}", double_quote(&v)))
+            .collect();
+        return Some(format!("declare {flag} {name}=({})\n", elements.join(" ")));
+    }
+    let var = ctx
+        .vars
+        .read()
+        .ok()
+        .and_then(|vars| vars.get(name).cloned())?;
+    let mut attrs = String::new();
+    if var.readonly {
+        attrs.push('r');
+    }
+    if var.exported {
+        attrs.push('x');
+    }
+    let attrs = if attrs.is_empty() {
+        "--".to_string()
+    } else {
+        format!("-{attrs}")
+    };
+    Some(format!(
+        "declare {attrs} {name}={}\n",
+        double_quote(&var.value)
+    ))
 }
 
-pub fn is_variable_readonly(name: &str) -> bool {
-    get_variable_info(name)
-        .map(|info| info.attributes.readonly)
-        .unwrap_or(false)
+fn double_quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
 }
 
-pub fn is_variable_exported(name: &str) -> bool {
-    get_variable_info(name)
-        .map(|info| info.attributes.export)
-        .unwrap_or(false)
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
-
-
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_compound_values_respecting_quotes() {
+        assert_eq!(
+            split_words("a 'b c' [k]=\"x y\""),
+            vec!["a", "'b c'", "[k]=\"x y\""]
+        );
+        assert_eq!(unquote("'it'\\''s'"), "it's");
+    }
 }
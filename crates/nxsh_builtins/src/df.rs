@@ -1,8 +1,601 @@
-//! `df` command - disk free space information
+//! `df` builtin - report the space used and free on mounted file systems
+//!
+//! Syntax:
+//!   df [-ahHiTk] [-t TYPE] [-x TYPE] [--output[=FIELD,...]] [FILE...]
+//!
+//! Without FILEs every mounted file system with blocks is listed, once per
+//! device; with them, the file systems holding each FILE. Sizes are counted
+//! in 1K blocks, or with `-h` and `-H` shown with a unit in powers of 1024
+//! or 1000. `-i` lists inodes instead of blocks and `-T` adds each file
+//! system's type. `-a` keeps the pseudo file systems and the devices
+//! mounted more than once, and `-t` and `-x` keep only or leave out the
+//! given types; both may be repeated or take a comma separated list. `-k`
+//! is accepted for compatibility.
+//!
+//! `--output` picks the columns and their order from source, fstype,
+//! itotal, iused, iavail, ipcent, size, used, avail, pcent, file and
+//! target; alone it shows them all. It cannot be combined with `-i` or
+//! `-T`, whose columns it can name.
+//!
+//! The mounts and their usage come from [`nxsh_hal::mounts`]:
+//! `/proc/self/mountinfo` and `statvfs` on Linux, `getmntinfo` on macOS
+//! and the volume APIs on Windows.
+//!
+//! In a structured pipeline `df` takes the same `-a`, `-t` and `-x` options
+//! and gives a table with the columns filesystem, type, mount, size, used,
+//! available, use%, inodes, iused, ifree and iuse%:
+//!
+//!   df | where use% -gt 90 | select mount use%
+//!
+//! The exit status is 0 on success and 1 for an invalid option or when a
+//! FILE or the mounts could not be read.
 
-use crate::common::{BuiltinContext, BuiltinResult};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::mounts::{fs_usage, list_mounts, mount_containing};
+use nxsh_hal::{FsUsage, MountEntry};
+use std::io::{self, Write};
+use std::path::Path;
 
-pub fn execute(_args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    println!("df command not yet fully implemented");
-    Ok(0)
+/// The `df` builtin command implementation
+pub struct DfCommand;
+
+impl Builtin for DfCommand {
+    fn name(&self) -> &'static str {
+        "df"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Report file system disk space usage"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show the size, used and available space, or the inodes, of every mounted file \
+         system or of those holding the given files."
+    }
+
+    fn usage(&self) -> &'static str {
+        "df [-ahHiTk] [-t TYPE] [-x TYPE] [--output[=FIELD,...]] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Report disk space. Use 'df -hT' for readable sizes with the file system types, \
+         'df -h .' for the file system you are on, 'df --output=target,pcent' for chosen \
+         columns, or 'df | where use% -gt 90' in a structured pipeline."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
+    }
+}
+
+/// Run df for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args, &std::env::current_dir()?);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
+}
+
+/// A mounted file system and its usage
+pub(crate) struct Filesystem {
+    pub(crate) mount: MountEntry,
+    pub(crate) usage: FsUsage,
+    /// The FILE argument it was found for
+    pub(crate) file: Option<String>,
+}
+
+/// The file systems `args` select, for the structured `df`
+pub(crate) fn selected_filesystems(args: &[String], cwd: &Path) -> Result<Vec<Filesystem>, String> {
+    Options::parse(args)?.filesystems(cwd)
+}
+
+/// A size as `df -h` shows it, rounded up to a tenth below 10 and to a
+/// whole unit above, in powers of `base`
+pub(crate) fn human_size(bytes: u64, base: u64) -> String {
+    let units: [&str; 6] = if base == 1000 {
+        ["k", "M", "G", "T", "P", "E"]
+    } else {
+        ["K", "M", "G", "T", "P", "E"]
+    };
+    if bytes < base {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / base as f64;
+    let mut unit = 0;
+    while value >= base as f64 && unit < units.len() - 1 {
+        value /= base as f64;
+        unit += 1;
+    }
+    if value < 10.0 {
+        let value = (value * 10.0).ceil() / 10.0;
+        if value < 10.0 {
+            return format!("{value:.1}{}", units[unit]);
+        }
+    }
+    format!("{:.0}{}", value.ceil(), units[unit])
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+fn run(args: &[String], cwd: &Path) -> Outcome {
+    let listing = Options::parse(args).and_then(|options| {
+        let filesystems = options.filesystems(cwd)?;
+        Ok(render(&filesystems, &options))
+    });
+    match listing {
+        Ok(stdout) => Outcome {
+            stdout,
+            stderr: String::new(),
+            status: 0,
+        },
+        Err(message) => Outcome {
+            stdout: String::new(),
+            stderr: format!("df: {message}\n"),
+            status: 1,
+        },
+    }
+}
+
+/// Parsed command line
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// `-a`: pseudo file systems and repeated devices too
+    all: bool,
+    /// `-h` or `-H`: the power sizes are shown in
+    human: Option<u64>,
+    /// `-i`: inodes instead of blocks
+    inodes: bool,
+    /// `-T`: a column with the type
+    print_type: bool,
+    /// `-t`: only these types
+    types: Vec<String>,
+    /// `-x`: not these types
+    exclude: Vec<String>,
+    /// `--output`: the columns to show
+    output: Option<Vec<Field>>,
+    files: Vec<String>,
+}
+
+/// A column `--output` can name
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Source,
+    Fstype,
+    Itotal,
+    Iused,
+    Iavail,
+    Ipcent,
+    Size,
+    Used,
+    Avail,
+    Pcent,
+    File,
+    Target,
+}
+
+impl Field {
+    const ALL: [Field; 12] = [
+        Field::Source,
+        Field::Fstype,
+        Field::Itotal,
+        Field::Iused,
+        Field::Iavail,
+        Field::Ipcent,
+        Field::Size,
+        Field::Used,
+        Field::Avail,
+        Field::Pcent,
+        Field::File,
+        Field::Target,
+    ];
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "source" => Field::Source,
+            "fstype" => Field::Fstype,
+            "itotal" => Field::Itotal,
+            "iused" => Field::Iused,
+            "iavail" => Field::Iavail,
+            "ipcent" => Field::Ipcent,
+            "size" => Field::Size,
+            "used" => Field::Used,
+            "avail" => Field::Avail,
+            "pcent" => Field::Pcent,
+            "file" => Field::File,
+            "target" => Field::Target,
+            _ => return None,
+        })
+    }
+
+    /// Whether the column is text, aligned to the left
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Source | Field::Fstype | Field::File | Field::Target
+        )
+    }
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (name, attached) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |option: &str| {
+                attached
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| format!("option requires an argument -- '{option}'"))
+            };
+            match name {
+                "--all" => options.all = true,
+                "--human-readable" => options.human = Some(1024),
+                "--si" => options.human = Some(1000),
+                "--inodes" => options.inodes = true,
+                "--print-type" => options.print_type = true,
+                "--type" => add_types(&mut options.types, &value("type")?),
+                "--exclude-type" => add_types(&mut options.exclude, &value("exclude-type")?),
+                "--output" => options.output = Some(parse_fields(attached.as_deref())?),
+                "--" => {
+                    options.files.extend(args.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 => {
+                    for (at, flag) in flags.char_indices().skip(1) {
+                        match flag {
+                            'a' => options.all = true,
+                            'h' => options.human = Some(1024),
+                            'H' => options.human = Some(1000),
+                            'i' => options.inodes = true,
+                            'T' => options.print_type = true,
+                            'k' => options.human = None,
+                            't' | 'x' => {
+                                // The type may follow in the same word
+                                let rest = &flags[at + 1..];
+                                let types = if rest.is_empty() {
+                                    value(&flag.to_string())?
+                                } else {
+                                    rest.to_string()
+                                };
+                                let list = if flag == 't' {
+                                    &mut options.types
+                                } else {
+                                    &mut options.exclude
+                                };
+                                add_types(list, &types);
+                                break;
+                            }
+                            other => return Err(format!("invalid option -- '{other}'")),
+                        }
+                    }
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        if options.output.is_some() && (options.inodes || options.print_type) {
+            return Err("options --output and -i/-T are mutually exclusive".to_string());
+        }
+        Ok(options)
+    }
+
+    /// The columns shown, in order
+    fn fields(&self) -> Vec<Field> {
+        if let Some(output) = &self.output {
+            return output.clone();
+        }
+        let mut fields = vec![Field::Source];
+        if self.print_type {
+            fields.push(Field::Fstype);
+        }
+        fields.extend(if self.inodes {
+            [Field::Itotal, Field::Iused, Field::Iavail, Field::Ipcent]
+        } else {
+            [Field::Size, Field::Used, Field::Avail, Field::Pcent]
+        });
+        fields.push(Field::Target);
+        fields
+    }
+
+    fn wants(&self, fs_type: &str) -> bool {
+        (self.types.is_empty() || self.types.iter().any(|wanted| wanted == fs_type))
+            && !self.exclude.iter().any(|excluded| excluded == fs_type)
+    }
+
+    fn filesystems(&self, cwd: &Path) -> Result<Vec<Filesystem>, String> {
+        if !self.files.is_empty() {
+            let mut listed = Vec::new();
+            for file in &self.files {
+                let path = cwd.join(file);
+                if let Err(e) = std::fs::metadata(&path) {
+                    return Err(format!("{file}: {}", io_message(&e)));
+                }
+                let mount = mount_containing(&path)
+                    .map_err(|e| format!("{file}: {e}"))?
+                    .ok_or_else(|| format!("{file}: no mounted file system holds it"))?;
+                let usage = fs_usage(&path).map_err(|e| format!("{file}: {e}"))?;
+                if self.wants(&mount.fs_type) {
+                    listed.push(Filesystem {
+                        mount,
+                        usage,
+                        file: Some(file.clone()),
+                    });
+                }
+            }
+            return Ok(listed);
+        }
+
+        let mounts = list_mounts().map_err(|e| e.to_string())?;
+        let mut listed: Vec<Filesystem> = Vec::new();
+        for mount in mounts {
+            if !self.wants(&mount.fs_type) {
+                continue;
+            }
+            // A later mount on the same point hides the earlier one
+            listed.retain(|listed| listed.mount.mount_point != mount.mount_point);
+            let Ok(usage) = fs_usage(&mount.mount_point) else {
+                continue;
+            };
+            listed.push(Filesystem {
+                mount,
+                usage,
+                file: None,
+            });
+        }
+        if !self.all {
+            // Pseudo file systems have no blocks
+            listed.retain(|listed| listed.usage.total > 0);
+            // A device mounted in several places shows once, where its path
+            // is shortest
+            let depth = |listed: &Filesystem| listed.mount.mount_point.components().count();
+            let mut kept: Vec<Filesystem> = Vec::with_capacity(listed.len());
+            for filesystem in listed {
+                let same = kept.iter().position(|kept| {
+                    filesystem.mount.device_number.is_some()
+                        && kept.mount.device_number == filesystem.mount.device_number
+                });
+                match same {
+                    Some(at) if depth(&filesystem) < depth(&kept[at]) => kept[at] = filesystem,
+                    Some(_) => {}
+                    None => kept.push(filesystem),
+                }
+            }
+            listed = kept;
+        }
+        Ok(listed)
+    }
+}
+
+/// The columns of `--output=LIST`, every one without a LIST
+fn parse_fields(list: Option<&str>) -> Result<Vec<Field>, String> {
+    let Some(list) = list else {
+        return Ok(Field::ALL.to_vec());
+    };
+    let mut fields = Vec::new();
+    for name in list.split(',').filter(|name| !name.is_empty()) {
+        let field =
+            Field::parse(name).ok_or_else(|| format!("option --output: field '{name}' unknown"))?;
+        if fields.contains(&field) {
+            return Err(format!(
+                "option --output: field '{name}' used more than once"
+            ));
+        }
+        fields.push(field);
+    }
+    Ok(fields)
+}
+
+fn add_types(list: &mut Vec<String>, types: &str) {
+    list.extend(
+        types
+            .split(',')
+            .filter(|fs_type| !fs_type.is_empty())
+            .map(str::to_string),
+    );
+}
+
+/// The table `df` prints, with the text columns left aligned and the
+/// numbers right aligned
+fn render(filesystems: &[Filesystem], options: &Options) -> String {
+    let size = |bytes: u64| match options.human {
+        Some(base) => human_size(bytes, base),
+        None => bytes.div_ceil(1024).to_string(),
+    };
+    let percent = |percent: Option<u64>| percent.map_or("-".to_string(), |p| format!("{p}%"));
+    let count = |count: Option<u64>| count.map_or("-".to_string(), |count| count.to_string());
+
+    let fields = options.fields();
+    let header = fields.iter().map(|field| match field {
+        Field::Source => "Filesystem",
+        Field::Fstype => "Type",
+        Field::Itotal => "Inodes",
+        Field::Iused => "IUsed",
+        Field::Iavail => "IFree",
+        Field::Ipcent => "IUse%",
+        Field::Size if options.human.is_some() => "Size",
+        Field::Size => "1K-blocks",
+        Field::Used => "Used",
+        // The default listing spells it out where there is room
+        Field::Avail if options.human.is_none() && options.output.is_none() => "Available",
+        Field::Avail => "Avail",
+        Field::Pcent => "Use%",
+        Field::File => "File",
+        Field::Target => "Mounted on",
+    });
+
+    let mut rows = vec![header.map(str::to_string).collect::<Vec<_>>()];
+    for Filesystem { mount, usage, file } in filesystems {
+        let inodes_used = usage
+            .files
+            .map(|files| files.saturating_sub(usage.files_free.unwrap_or(0)));
+        rows.push(
+            fields
+                .iter()
+                .map(|field| match field {
+                    Field::Source => mount.source.clone(),
+                    Field::Fstype => mount.fs_type.clone(),
+                    Field::Itotal => count(usage.files),
+                    Field::Iused => count(inodes_used),
+                    Field::Iavail => count(usage.files_free),
+                    Field::Ipcent => percent(usage.inode_use_percent()),
+                    Field::Size => size(usage.total),
+                    Field::Used => size(usage.used()),
+                    Field::Avail => size(usage.available),
+                    Field::Pcent => percent(usage.use_percent()),
+                    Field::File => file.clone().unwrap_or_else(|| "-".to_string()),
+                    Field::Target => mount.mount_point.display().to_string(),
+                })
+                .collect(),
+        );
+    }
+
+    let columns = fields.len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for row in &rows {
+        let mut cells = Vec::with_capacity(columns);
+        for (column, cell) in row.iter().enumerate() {
+            let width = widths[column];
+            cells.push(if column == columns - 1 && fields[column].is_text() {
+                cell.clone()
+            } else if fields[column].is_text() {
+                format!("{cell:<width$}")
+            } else {
+                format!("{cell:>width$}")
+            });
+        }
+        out.push_str(&cells.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_combine_and_take_types() {
+        let options =
+            Options::parse(&args(&["-hT", "-t", "ext4,xfs", "-xtmpfs", "/home"])).unwrap();
+        assert_eq!(options.human, Some(1024));
+        assert!(options.print_type);
+        assert_eq!(options.types, ["ext4", "xfs"]);
+        assert_eq!(options.exclude, ["tmpfs"]);
+        assert_eq!(options.files, ["/home"]);
+        assert!(options.wants("xfs"));
+        assert!(!options.wants("tmpfs"));
+
+        let options = Options::parse(&args(&["--si", "--exclude-type=proc"])).unwrap();
+        assert_eq!(options.human, Some(1000));
+        assert_eq!(options.exclude, ["proc"]);
+        assert!(Options::parse(&args(&["-q"])).is_err());
+        assert!(Options::parse(&args(&["-t"])).is_err());
+    }
+
+    #[test]
+    fn output_names_the_columns() {
+        let options = Options::parse(&args(&["--output=target,ipcent,pcent", "/"])).unwrap();
+        assert_eq!(
+            options.fields(),
+            [Field::Target, Field::Ipcent, Field::Pcent]
+        );
+        assert_eq!(options.files, ["/"]);
+        let options = Options::parse(&args(&["--output"])).unwrap();
+        assert_eq!(options.fields(), Field::ALL);
+        assert_eq!(
+            Options::parse(&args(&["-hT"])).unwrap().fields(),
+            [
+                Field::Source,
+                Field::Fstype,
+                Field::Size,
+                Field::Used,
+                Field::Avail,
+                Field::Pcent,
+                Field::Target
+            ]
+        );
+
+        let error = |list: &[&str]| Options::parse(&args(list)).unwrap_err();
+        assert_eq!(
+            error(&["--output=size,bogus"]),
+            "option --output: field 'bogus' unknown"
+        );
+        assert_eq!(
+            error(&["--output=size,size"]),
+            "option --output: field 'size' used more than once"
+        );
+        assert_eq!(
+            error(&["-i", "--output=size"]),
+            "options --output and -i/-T are mutually exclusive"
+        );
+    }
+
+    #[test]
+    fn renders_the_chosen_columns() {
+        let filesystem = Filesystem {
+            mount: MountEntry {
+                source: "/dev/sda1".to_string(),
+                mount_point: "/home".into(),
+                fs_type: "ext4".to_string(),
+                options: vec!["rw".to_string()],
+                device_number: Some((8, 1)),
+                uuid: None,
+                label: None,
+            },
+            usage: FsUsage {
+                total: 10 * 1024 * 1024,
+                free: 4 * 1024 * 1024,
+                available: 3 * 1024 * 1024,
+                files: Some(1000),
+                files_free: Some(750),
+            },
+            file: Some("notes".to_string()),
+        };
+        let options = Options::parse(&args(&["--output=file,size,ipcent,target"])).unwrap();
+        assert_eq!(
+            render(&[filesystem], &options),
+            "File  1K-blocks IUse% Mounted on\n\
+             notes     10240   25% /home\n"
+        );
+    }
+
+    #[test]
+    fn human_sizes_round_up() {
+        assert_eq!(human_size(0, 1024), "0");
+        assert_eq!(human_size(1000, 1024), "1000");
+        assert_eq!(human_size(1536, 1024), "1.5K");
+        assert_eq!(human_size(1537, 1024), "1.6K");
+        assert_eq!(human_size(42_131_046_400, 1024), "40G");
+        assert_eq!(human_size(10_239 * 1024, 1024), "10M");
+        assert_eq!(human_size(1500, 1000), "1.5k");
+    }
 }
@@ -1,88 +1,1193 @@
-//! `diff` command  Edisplay line differences between two files.
+//! `diff` builtin - compare files line by line
 //!
-//! Minimal subset implemented:
-//!   diff FILE1 FILE2
-//!   • Compares files line-by-line.
-//!   • Output style similar to traditional diff default:
-//!       < line (only in FILE1)
-//!       > line (only in FILE2)
-//!   • Unified/side-by-side modes and option flags are not supported.
-//!   • FILE of "-" refers to STDIN (only for FILE1; FILE2 must be path).
-
-use anyhow::{anyhow, Result};
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
-
-pub fn diff_cli(args: &[String]) -> Result<()> {
-    if args.len() < 2 {
-        return Err(anyhow!("diff: missing file operands"));
-    }
-    let f1 = &args[0];
-    let f2 = &args[1];
-
-    let reader1: Box<dyn BufRead> = if f1 == "-" {
-        Box::new(BufReader::new(io::stdin()))
-    } else {
-        Box::new(BufReader::new(File::open(Path::new(f1))?))
+//! Syntax:
+//!   diff [OPTION...] FILE1 FILE2
+//!
+//! Differences are printed in the normal format by default, in the unified
+//! format with `-u`/`-U NUM`, in the context format with `-c`/`-C NUM`, or
+//! only as a `Files ... differ` line with `-q`. Lines are matched with
+//! Myers' algorithm in linear space, and `-i`, `-b` and `-w` make case and
+//! white space count for nothing when lines are matched.
+//!
+//! Either FILE may be `-` for standard input. When one FILE is a directory
+//! the file of the other's base name inside it is compared; when both are,
+//! their entries are compared in name order, recursing into subdirectories
+//! with `-r`. `-N` treats a file missing on one side as empty, and `-x`
+//! leaves out entries whose names match a pattern. A file holding a NUL byte
+//! is binary and only reported as differing unless `-a` is given.
+//!
+//! `--color` paints headers, hunk ranges, removed and added lines with the
+//! theme's `diff.header`, `diff.hunk`, `diff.removed` and `diff.added`
+//! styles, falling back to GNU diff's colors.
+//!
+//! The exit status is 0 when the inputs are the same, 1 when they differ and
+//! 2 on trouble.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crossterm::style::ContentStyle;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::{Index, IndexMut, Range};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The `diff` builtin command implementation
+pub struct DiffCommand;
+
+impl Builtin for DiffCommand {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compare files line by line"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compare two files, or with -r two directory trees, and print the differing \
+         lines in the normal, unified (-u) or context (-c) format, or only whether \
+         they differ (-q)."
+    }
+
+    fn usage(&self) -> &'static str {
+        "diff [-abiNqrsuw] [-c | -C NUM | -U NUM] [-x PATTERN] [-X FILE] \
+         [--label LABEL] [--color[=WHEN]] FILE1 FILE2"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compare files line by line. Use 'diff -u OLD NEW' for a patch-style listing \
+         and 'diff -r DIR1 DIR2' to compare trees."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run diff for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome {
+                stdout: Vec::new(),
+                stderr: format!("diff: {message}\ndiff: Try 'diff --help' for more information.\n")
+                    .into_bytes(),
+                status: 2,
+            }
+        }
+    };
+    let colors = options.color.then(Colors::load);
+    let mut diff = Diff {
+        options: &options,
+        colors,
+        cwd,
+        stdin,
+        stdin_data: None,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        differ: false,
+        trouble: false,
+    };
+    diff.operands(&options.operands[0], &options.operands[1]);
+    let status = match (diff.trouble, diff.differ) {
+        (true, _) => 2,
+        (false, true) => 1,
+        (false, false) => 0,
     };
-    let reader2: Box<dyn BufRead> = Box::new(BufReader::new(File::open(Path::new(f2))?));
+    Outcome {
+        stdout: diff.stdout,
+        stderr: diff.stderr,
+        status,
+    }
+}
+
+/// How differences are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Normal,
+    /// `-u`, with this many lines of context
+    Unified(usize),
+    /// `-c`, with this many lines of context
+    Context(usize),
+    /// `-q`
+    Brief,
+}
+
+/// Parsed command line
+#[derive(Debug)]
+struct Options {
+    format: Format,
+    recursive: bool,
+    new_file: bool,
+    ignore_case: bool,
+    ignore_space_change: bool,
+    ignore_all_space: bool,
+    text: bool,
+    report_identical: bool,
+    color: bool,
+    labels: Vec<String>,
+    exclude: Vec<glob::Pattern>,
+    /// The options as given, repeated before each pair of files compared
+    /// inside directories
+    switches: Vec<String>,
+    operands: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            format: Format::Normal,
+            recursive: false,
+            new_file: false,
+            ignore_case: false,
+            ignore_space_change: false,
+            ignore_all_space: false,
+            text: false,
+            report_identical: false,
+            color: false,
+            labels: Vec::new(),
+            exclude: Vec::new(),
+            switches: Vec::new(),
+            operands: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                options.operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            if arg == "-" || !arg.starts_with('-') {
+                options.operands.push(arg.clone());
+                continue;
+            }
+            options.switches.push(arg.clone());
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let mut value = || match inline.clone() {
+                    Some(value) => Ok(value),
+                    None => {
+                        let value = iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("option '--{name}' requires an argument"))?;
+                        options.switches.push(value.clone());
+                        Ok::<_, String>(value)
+                    }
+                };
+                match name {
+                    "unified" => {
+                        let lines = inline.as_deref().map_or(Ok(3), context_lines)?;
+                        options.format = Format::Unified(lines);
+                    }
+                    "context" => {
+                        let lines = inline.as_deref().map_or(Ok(3), context_lines)?;
+                        options.format = Format::Context(lines);
+                    }
+                    "label" => {
+                        let label = value()?;
+                        options.labels.push(label);
+                    }
+                    "exclude" => {
+                        let pattern = glob_pattern(&value()?)?;
+                        options.exclude.push(pattern);
+                    }
+                    "exclude-from" => {
+                        let file = value()?;
+                        options.exclude_from(&file)?;
+                    }
+                    "color" | "colour" => {
+                        options.color = match inline.as_deref() {
+                            None | Some("auto") => {
+                                io::stdout().is_terminal()
+                                    && !std::env::var("TERM").is_ok_and(|t| t == "dumb")
+                            }
+                            Some("always") => true,
+                            Some("never") => false,
+                            Some(other) => {
+                                return Err(format!("invalid argument '{other}' for '--color'"))
+                            }
+                        }
+                    }
+                    _ if inline.is_some() => {
+                        return Err(format!("option '--{name}' doesn't allow an argument"))
+                    }
+                    "normal" => options.format = Format::Normal,
+                    "brief" => options.flag('q')?,
+                    "recursive" => options.flag('r')?,
+                    "new-file" => options.flag('N')?,
+                    "ignore-case" => options.flag('i')?,
+                    "ignore-space-change" => options.flag('b')?,
+                    "ignore-all-space" => options.flag('w')?,
+                    "text" => options.flag('a')?,
+                    "report-identical-files" => options.flag('s')?,
+                    _ => return Err(format!("unrecognized option '--{name}'")),
+                }
+                continue;
+            }
+            let letters: Vec<char> = arg[1..].chars().collect();
+            for (index, &letter) in letters.iter().enumerate() {
+                if !matches!(letter, 'U' | 'C' | 'x' | 'X') {
+                    options.flag(letter)?;
+                    continue;
+                }
+                let rest: String = letters[index + 1..].iter().collect();
+                let value = match rest.is_empty() {
+                    true => {
+                        let value = iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("option requires an argument -- '{letter}'"))?;
+                        options.switches.push(value.clone());
+                        value
+                    }
+                    false => rest,
+                };
+                match letter {
+                    'U' => options.format = Format::Unified(context_lines(&value)?),
+                    'C' => options.format = Format::Context(context_lines(&value)?),
+                    'x' => options.exclude.push(glob_pattern(&value)?),
+                    _ => options.exclude_from(&value)?,
+                }
+                break;
+            }
+        }
+        match options.operands.len() {
+            0 => Err("missing operand".to_string()),
+            1 => Err(format!("missing operand after '{}'", options.operands[0])),
+            2 => Ok(options),
+            _ => Err(format!("extra operand '{}'", options.operands[2])),
+        }
+    }
+
+    fn flag(&mut self, letter: char) -> Result<(), String> {
+        match letter {
+            'u' => self.format = Format::Unified(3),
+            'c' => self.format = Format::Context(3),
+            'q' => self.format = Format::Brief,
+            'r' => self.recursive = true,
+            'N' => self.new_file = true,
+            'i' => self.ignore_case = true,
+            'b' => self.ignore_space_change = true,
+            'w' => self.ignore_all_space = true,
+            'a' => self.text = true,
+            's' => self.report_identical = true,
+            _ => return Err(format!("invalid option -- '{letter}'")),
+        }
+        Ok(())
+    }
+
+    fn exclude_from(&mut self, file: &str) -> Result<(), String> {
+        let text = fs::read_to_string(file).map_err(|e| format!("{file}: {}", io_message(&e)))?;
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            self.exclude.push(glob_pattern(line)?);
+        }
+        Ok(())
+    }
+
+    /// Whether entries called NAME are left out of directory comparisons
+    fn excluded(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+fn context_lines(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid context length '{value}'"))
+}
+
+fn glob_pattern(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|e| format!("invalid pattern '{value}': {e}"))
+}
+
+/// How one kind of output is painted
+#[derive(Clone, Copy)]
+enum Paint {
+    /// An SGR sequence, such as GNU diff's defaults
+    Sgr(&'static str),
+    /// A theme style
+    Style(ContentStyle),
+}
+
+impl Paint {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Paint::Sgr(sgr) => format!("\x1b[{sgr}m{text}\x1b[m"),
+            Paint::Style(style) => style.apply(text).to_string(),
+        }
+    }
+}
 
-    diff_streams(reader1, reader2)?;
-    Ok(())
+/// The colors of `--color`
+struct Colors {
+    header: Paint,
+    hunk: Paint,
+    removed: Paint,
+    added: Paint,
+}
+
+impl Colors {
+    /// The active theme's colors, or GNU diff's where it has none
+    fn load() -> Self {
+        let mut colors = Colors {
+            header: Paint::Sgr("1"),
+            hunk: Paint::Sgr("36"),
+            removed: Paint::Sgr("31"),
+            added: Paint::Sgr("32"),
+        };
+        let theme_name = nxsh_ui::UiConfig::default().theme_name;
+        if let Ok(theme) = nxsh_ui::get_theme(&theme_name) {
+            let style = |name: &str| {
+                theme
+                    .styles
+                    .get(name)
+                    .map(|style| Paint::Style(style.clone().into()))
+            };
+            if let Some(paint) = style("diff.header") {
+                colors.header = paint;
+            }
+            if let Some(paint) = style("diff.hunk") {
+                colors.hunk = paint;
+            }
+            if let Some(paint) = style("diff.removed") {
+                colors.removed = paint;
+            }
+            if let Some(paint) = style("diff.added") {
+                colors.added = paint;
+            }
+        }
+        colors
+    }
 }
 
-fn diff_streams<R1: BufRead, R2: BufRead>(mut r1: R1, mut r2: R2) -> Result<()> {
-    let mut l1 = String::new();
-    let mut l2 = String::new();
+/// One side of a comparison
+struct Input {
+    /// The name as printed
+    name: String,
+    /// `None` for standard input
+    path: Option<PathBuf>,
+}
+
+/// A run of lines that differ: lines A of the first file were replaced by
+/// lines B of the second, either of which may be empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    a: Range<usize>,
+    b: Range<usize>,
+}
+
+/// The state of one diff run
+struct Diff<'a> {
+    options: &'a Options,
+    colors: Option<Colors>,
+    cwd: &'a Path,
+    stdin: &'a mut dyn Read,
+    /// Standard input once read, as it can be named only once
+    stdin_data: Option<Vec<u8>>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    differ: bool,
+    trouble: bool,
+}
+
+impl Diff<'_> {
+    fn error(&mut self, text: String) {
+        self.stderr.extend(format!("diff: {text}\n").as_bytes());
+        self.trouble = true;
+    }
+
+    fn input(&self, name: &str) -> Input {
+        Input {
+            name: name.to_string(),
+            path: (name != "-").then(|| self.cwd.join(name)),
+        }
+    }
+
+    /// Compare the two operands
+    fn operands(&mut self, first: &str, second: &str) {
+        let (a, b) = (self.input(first), self.input(second));
+        let is_dir = |input: &Input| input.path.as_deref().is_some_and(Path::is_dir);
+        let missing = |input: &Input| input.path.as_deref().is_some_and(|p| !p.exists());
+        let (a_missing, b_missing) = (missing(&a), missing(&b));
+        if (a_missing || b_missing) && (!self.options.new_file || (a_missing && b_missing)) {
+            for input in [&a, &b].into_iter().filter(|input| missing(input)) {
+                let e = io::Error::from(io::ErrorKind::NotFound);
+                self.error(format!("{}: {}", input.name, io_message(&e)));
+            }
+            return;
+        }
+        // With -N a missing operand stands in for whatever the other one is
+        let a_dir = is_dir(&a) || (a_missing && is_dir(&b));
+        let b_dir = is_dir(&b) || (b_missing && is_dir(&a));
+        match (a_dir, b_dir) {
+            (true, true) => self.directories(&a, &b),
+            (true, false) | (false, true) => {
+                let (dir, file) = if a_dir { (&a, &b) } else { (&b, &a) };
+                if file.path.is_none() {
+                    return self.error("cannot compare '-' to a directory".to_string());
+                }
+                let base = Path::new(&file.name)
+                    .file_name()
+                    .map(|base| base.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let inner = self.input(&join(&dir.name, &base));
+                match a_dir {
+                    true => self.files(&inner, &b, false),
+                    false => self.files(&a, &inner, false),
+                }
+            }
+            (false, false) => self.files(&a, &b, false),
+        }
+    }
 
-    loop {
-        let eof1 = r1.read_line(&mut l1)? == 0;
-        let eof2 = r2.read_line(&mut l2)? == 0;
-        if eof1 && eof2 { break; }
+    /// Compare the entries of two directories, either of which may be
+    /// missing with `-N`
+    fn directories(&mut self, a: &Input, b: &Input) {
+        let (Some(a_entries), Some(b_entries)) = (self.entries(a), self.entries(b)) else {
+            return;
+        };
+        let mut names: Vec<&String> = a_entries.keys().chain(b_entries.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let (a_kind, b_kind) = (a_entries.get(name), b_entries.get(name));
+            let a_child = self.input(&join(&a.name, name));
+            let b_child = self.input(&join(&b.name, name));
+            match (a_kind, b_kind) {
+                (Some(&a_dir), Some(&b_dir)) if a_dir && b_dir => {
+                    if self.options.recursive {
+                        self.directories(&a_child, &b_child);
+                    } else {
+                        self.line(
+                            None,
+                            &format!(
+                                "Common subdirectories: {} and {}",
+                                a_child.name, b_child.name
+                            ),
+                        );
+                    }
+                }
+                (Some(&a_dir), Some(&b_dir)) if a_dir != b_dir => {
+                    let kind = |dir: bool| if dir { "directory" } else { "regular file" };
+                    self.line(
+                        None,
+                        &format!(
+                            "File {} is a {} while file {} is a {}",
+                            a_child.name,
+                            kind(a_dir),
+                            b_child.name,
+                            kind(b_dir)
+                        ),
+                    );
+                    self.differ = true;
+                }
+                (Some(_), Some(_)) => self.files(&a_child, &b_child, true),
+                (Some(&dir), None) | (None, Some(&dir)) => {
+                    if !self.options.new_file || (dir && !self.options.recursive) {
+                        let parent = if a_kind.is_some() { &a.name } else { &b.name };
+                        self.line(None, &format!("Only in {parent}: {name}"));
+                        self.differ = true;
+                    } else if dir {
+                        self.directories(&a_child, &b_child);
+                    } else {
+                        self.files(&a_child, &b_child, true);
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+    }
 
-        if !eof1 { trim_newline(&mut l1); }
-        if !eof2 { trim_newline(&mut l2); }
+    /// The entries of a directory that are not excluded, and whether each is
+    /// a directory; none for one that is missing with `-N`
+    fn entries(&mut self, dir: &Input) -> Option<HashMap<String, bool>> {
+        let path = dir.path.as_deref()?;
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.options.new_file => {
+                return Some(HashMap::new())
+            }
+            Err(e) => {
+                self.error(format!("{}: {}", dir.name, io_message(&e)));
+                return None;
+            }
+        };
+        Some(
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !self.options.excluded(name))
+                .map(|name| {
+                    let is_dir = path.join(&name).is_dir();
+                    (name, is_dir)
+                })
+                .collect(),
+        )
+    }
 
-        match (eof1, eof2) {
-            (false, false) => {
-                if l1 != l2 {
-                    println!("< {l1}");
-                    println!("> {l2}");
+    /// The contents of INPUT; a missing file reads as empty with `-N`
+    fn read(&mut self, input: &Input) -> Option<Vec<u8>> {
+        let Some(path) = &input.path else {
+            if self.stdin_data.is_none() {
+                let mut data = Vec::new();
+                if let Err(e) = self.stdin.read_to_end(&mut data) {
+                    self.error(format!("-: {}", io_message(&e)));
+                    return None;
                 }
-                l1.clear();
-                l2.clear();
+                self.stdin_data = Some(data);
             }
-            (false, true) => {
-                println!("< {l1}");
-                l1.clear();
+            return self.stdin_data.clone();
+        };
+        match fs::read(path) {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.options.new_file => {
+                Some(Vec::new())
             }
-            (true, false) => {
-                println!("> {l2}");
-                l2.clear();
+            Err(e) => {
+                self.error(format!("{}: {}", input.name, io_message(&e)));
+                None
             }
-            _ => {}
         }
     }
-    Ok(())
+
+    /// Compare two files; IN_DIR is set for a pair found inside directories,
+    /// which is announced with the options first
+    fn files(&mut self, a: &Input, b: &Input, in_dir: bool) {
+        let (Some(a_data), Some(b_data)) = (self.read(a), self.read(b)) else {
+            return;
+        };
+        if a_data == b_data {
+            if self.options.report_identical {
+                self.line(
+                    None,
+                    &format!("Files {} and {} are identical", a.name, b.name),
+                );
+            }
+            return;
+        }
+        let binary = !self.options.text && (a_data.contains(&0) || b_data.contains(&0));
+        if binary || self.options.format == Format::Brief {
+            let what = if binary { "Binary files" } else { "Files" };
+            self.line(None, &format!("{what} {} and {} differ", a.name, b.name));
+            self.differ = true;
+            return;
+        }
+
+        let a_lines: Vec<&[u8]> = a_data.split_inclusive(|&byte| byte == b'\n').collect();
+        let b_lines: Vec<&[u8]> = b_data.split_inclusive(|&byte| byte == b'\n').collect();
+        let changes = self.compare(&a_lines, &b_lines);
+        if changes.is_empty() {
+            if self.options.report_identical {
+                self.line(
+                    None,
+                    &format!("Files {} and {} are identical", a.name, b.name),
+                );
+            }
+            return;
+        }
+        self.differ = true;
+        if in_dir {
+            let mut command = vec!["diff".to_string()];
+            command.extend(self.options.switches.iter().cloned());
+            command.push(a.name.clone());
+            command.push(b.name.clone());
+            let header = self.colors.as_ref().map(|c| c.header);
+            self.line(header.as_ref(), &command.join(" "));
+        }
+        match self.options.format {
+            Format::Normal => self.normal(&a_lines, &b_lines, &changes),
+            Format::Unified(context) => {
+                self.file_header("---", 0, a);
+                self.file_header("+++", 1, b);
+                self.unified(&a_lines, &b_lines, &changes, context);
+            }
+            Format::Context(context) => {
+                self.file_header("***", 0, a);
+                self.file_header("---", 1, b);
+                self.context(&a_lines, &b_lines, &changes, context);
+            }
+            Format::Brief => {}
+        }
+    }
+
+    /// The runs of lines that differ between A and B, in order
+    fn compare(&self, a: &[&[u8]], b: &[&[u8]]) -> Vec<Change> {
+        // Lines are compared by number, equal numbers for lines that are the
+        // same once -i, -b and -w have had their way
+        let mut ids: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut number = |line: &[u8]| {
+            let key = self.key(line);
+            let next = ids.len();
+            *ids.entry(key).or_insert(next)
+        };
+        let a_ids: Vec<usize> = a.iter().map(|line| number(line)).collect();
+        let b_ids: Vec<usize> = b.iter().map(|line| number(line)).collect();
+        changes(&a_ids, &b_ids)
+    }
+
+    fn key(&self, line: &[u8]) -> Vec<u8> {
+        let options = self.options;
+        let (body, newline) = match line.strip_suffix(b"\n") {
+            Some(body) => (body, true),
+            None => (line, false),
+        };
+        let mut key = Vec::with_capacity(line.len());
+        if options.ignore_all_space {
+            key.extend(body.iter().filter(|byte| !byte.is_ascii_whitespace()));
+        } else if options.ignore_space_change {
+            let mut space = false;
+            for &byte in body {
+                if byte.is_ascii_whitespace() {
+                    space = true;
+                    continue;
+                }
+                if space {
+                    key.push(b' ');
+                }
+                space = false;
+                key.push(byte);
+            }
+        } else {
+            key.extend_from_slice(body);
+        }
+        if options.ignore_case {
+            key.make_ascii_lowercase();
+        }
+        if newline {
+            key.push(b'\n');
+        }
+        key
+    }
+
+    /// Print TEXT and a newline, in PAINT if colors are on
+    fn line(&mut self, paint: Option<&Paint>, text: &str) {
+        let text = match paint {
+            Some(paint) => paint.apply(text),
+            None => text.to_string(),
+        };
+        self.stdout.extend(text.as_bytes());
+        self.stdout.push(b'\n');
+    }
+
+    /// Print LINE behind MARKER, noting a missing final newline
+    fn text_line(&mut self, marker: &str, line: &[u8], removed: Option<bool>) {
+        let body = line.strip_suffix(b"\n").unwrap_or(line);
+        let paint = match (&self.colors, removed) {
+            (Some(colors), Some(true)) => Some(&colors.removed),
+            (Some(colors), Some(false)) => Some(&colors.added),
+            _ => None,
+        };
+        match paint {
+            Some(paint) => {
+                let text = format!("{marker}{}", String::from_utf8_lossy(body));
+                self.stdout.extend(paint.apply(&text).as_bytes());
+            }
+            None => {
+                self.stdout.extend(marker.as_bytes());
+                self.stdout.extend(body);
+            }
+        }
+        self.stdout.push(b'\n');
+        if !line.ends_with(b"\n") {
+            self.stdout.extend(b"\\ No newline at end of file\n");
+        }
+    }
+
+    /// The `---`/`+++` or `***`/`---` line naming one file: its `--label`,
+    /// or its name and modification time
+    fn file_header(&mut self, marker: &str, side: usize, input: &Input) {
+        let text = match self.options.labels.get(side) {
+            Some(label) => format!("{marker} {label}"),
+            None => {
+                let time = match &input.path {
+                    Some(path) => fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH),
+                    None => SystemTime::now(),
+                };
+                let time = chrono::DateTime::<chrono::Local>::from(time);
+                format!(
+                    "{marker} {}\t{}",
+                    input.name,
+                    time.format("%Y-%m-%d %H:%M:%S%.9f %z")
+                )
+            }
+        };
+        let paint = self.colors.as_ref().map(|c| &c.header);
+        let text = match paint {
+            Some(paint) => paint.apply(&text),
+            None => text,
+        };
+        self.stdout.extend(text.as_bytes());
+        self.stdout.push(b'\n');
+    }
+
+    fn hunk_line(&mut self, text: &str) {
+        let text = match &self.colors {
+            Some(colors) => colors.hunk.apply(text),
+            None => text.to_string(),
+        };
+        self.stdout.extend(text.as_bytes());
+        self.stdout.push(b'\n');
+    }
+
+    /// The normal format: `2,3c2` and the lines on either side
+    fn normal(&mut self, a: &[&[u8]], b: &[&[u8]], changes: &[Change]) {
+        for change in changes {
+            let op = match (change.a.is_empty(), change.b.is_empty()) {
+                (false, true) => 'd',
+                (true, false) => 'a',
+                _ => 'c',
+            };
+            self.hunk_line(&format!(
+                "{}{op}{}",
+                normal_range(&change.a),
+                normal_range(&change.b)
+            ));
+            for line in &a[change.a.clone()] {
+                self.text_line("< ", line, Some(true));
+            }
+            if op == 'c' {
+                self.stdout.extend(b"---\n");
+            }
+            for line in &b[change.b.clone()] {
+                self.text_line("> ", line, Some(false));
+            }
+        }
+    }
+
+    /// The unified format: `@@ -1,3 +1,4 @@` and the lines of each hunk
+    /// marked ` `, `-` or `+`
+    fn unified(&mut self, a: &[&[u8]], b: &[&[u8]], changes: &[Change], context: usize) {
+        for hunk in hunks(changes, context) {
+            let (a_span, b_span) = spans(hunk, context, a.len());
+            self.hunk_line(&format!(
+                "@@ -{} +{} @@",
+                unified_range(&a_span),
+                unified_range(&b_span)
+            ));
+            let mut at = a_span.start;
+            for change in hunk {
+                for line in &a[at..change.a.start] {
+                    self.text_line(" ", line, None);
+                }
+                for line in &a[change.a.clone()] {
+                    self.text_line("-", line, Some(true));
+                }
+                for line in &b[change.b.clone()] {
+                    self.text_line("+", line, Some(false));
+                }
+                at = change.a.end;
+            }
+            for line in &a[at..a_span.end] {
+                self.text_line(" ", line, None);
+            }
+        }
+    }
+
+    /// The context format: each hunk's lines of the first file, then of the
+    /// second, marked ` `, `-`, `+` or `!` for changed ones
+    fn context(&mut self, a: &[&[u8]], b: &[&[u8]], changes: &[Change], context: usize) {
+        for hunk in hunks(changes, context) {
+            let (a_span, b_span) = spans(hunk, context, a.len());
+            self.hunk_line("***************");
+            self.hunk_line(&format!("*** {} ****", context_range(&a_span)));
+            if hunk.iter().any(|change| !change.a.is_empty()) {
+                self.context_side(a, &a_span, hunk, true);
+            }
+            self.hunk_line(&format!("--- {} ----", context_range(&b_span)));
+            if hunk.iter().any(|change| !change.b.is_empty()) {
+                self.context_side(b, &b_span, hunk, false);
+            }
+        }
+    }
+
+    fn context_side(&mut self, lines: &[&[u8]], span: &Range<usize>, hunk: &[Change], old: bool) {
+        let mut at = span.start;
+        for change in hunk {
+            let (range, other) = match old {
+                true => (&change.a, &change.b),
+                false => (&change.b, &change.a),
+            };
+            for line in &lines[at..range.start] {
+                self.text_line("  ", line, None);
+            }
+            let marker = match (other.is_empty(), old) {
+                (false, _) => "! ",
+                (true, true) => "- ",
+                (true, false) => "+ ",
+            };
+            for line in &lines[range.clone()] {
+                self.text_line(marker, line, Some(old));
+            }
+            at = range.end;
+        }
+        for line in &lines[at..span.end] {
+            self.text_line("  ", line, None);
+        }
+    }
 }
 
-fn trim_newline(s: &mut String) {
-    if s.ends_with('\n') { s.pop(); }
-    if s.ends_with('\r') { s.pop(); }
+fn join(dir: &str, name: &str) -> String {
+    match dir.ends_with('/') {
+        true => format!("{dir}{name}"),
+        false => format!("{dir}/{name}"),
+    }
+}
+
+/// Changes grouped into hunks: ones separated by no more than twice CONTEXT
+/// equal lines share a hunk
+fn hunks(changes: &[Change], context: usize) -> Vec<&[Change]> {
+    let mut hunks = Vec::new();
+    let mut start = 0;
+    for index in 1..=changes.len() {
+        let split = index == changes.len()
+            || changes[index].a.start - changes[index - 1].a.end > 2 * context;
+        if split {
+            hunks.push(&changes[start..index]);
+            start = index;
+        }
+    }
+    hunks
+}
+
+/// The lines of either file a hunk covers, context included
+fn spans(hunk: &[Change], context: usize, a_len: usize) -> (Range<usize>, Range<usize>) {
+    let (first, last) = (&hunk[0], &hunk[hunk.len() - 1]);
+    let before = first.a.start.min(context);
+    let after = (a_len - last.a.end).min(context);
+    (
+        first.a.start - before..last.a.end + after,
+        first.b.start - before..last.b.end + after,
+    )
+}
+
+/// `3`, `3,5`, or for no lines the number of the line before them
+fn normal_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 => range.start.to_string(),
+        1 => range.end.to_string(),
+        _ => format!("{},{}", range.start + 1, range.end),
+    }
+}
+
+/// `3`, `3,4` as start and length, or `2,0` for no lines after line 2
+fn unified_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => range.end.to_string(),
+        len => format!("{},{len}", range.start + 1),
+    }
+}
+
+/// `3,5` as first and last line, `5` for one line, or for no lines the
+/// number of the line before them
+fn context_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 | 1 => range.end.to_string(),
+        _ => format!("{},{}", range.start + 1, range.end),
+    }
+}
+
+/// The runs of elements that differ between A and B
+fn changes(a: &[usize], b: &[usize]) -> Vec<Change> {
+    let mut removed = vec![false; a.len()];
+    let mut added = vec![false; b.len()];
+    let max = (a.len() + b.len() + 1) / 2 + 1;
+    let mut forward = Frontier::new(max);
+    let mut backward = Frontier::new(max);
+    conquer(
+        a,
+        0..a.len(),
+        b,
+        0..b.len(),
+        &mut forward,
+        &mut backward,
+        &mut removed,
+        &mut added,
+    );
+
+    // Walk both sequences; unmarked elements pair up as the common
+    // subsequence, and each stretch of marked ones between them is a change
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && !removed[i] && !added[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (a_start, b_start) = (i, j);
+        while i < a.len() && removed[i] {
+            i += 1;
+        }
+        while j < b.len() && added[j] {
+            j += 1;
+        }
+        changes.push(Change {
+            a: a_start..i,
+            b: b_start..j,
+        });
+    }
+    changes
+}
+
+/// The furthest point reached on each diagonal `k` of the edit graph, for
+/// `k` between `-max` and `max`
+struct Frontier {
+    offset: isize,
+    x: Vec<usize>,
+}
+
+impl Frontier {
+    fn new(max: usize) -> Self {
+        Frontier {
+            offset: max as isize,
+            x: vec![0; 2 * max + 1],
+        }
+    }
+}
+
+impl Index<isize> for Frontier {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for Frontier {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+fn common_prefix(a: &[usize], b: &[usize]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn common_suffix(a: &[usize], b: &[usize]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Mark the elements of A and B in the given ranges that are not part of a
+/// longest common subsequence, splitting the problem at a middle snake as
+/// in Myers' linear space refinement
+#[allow(clippy::too_many_arguments)]
+fn conquer(
+    a: &[usize],
+    mut a_range: Range<usize>,
+    b: &[usize],
+    mut b_range: Range<usize>,
+    forward: &mut Frontier,
+    backward: &mut Frontier,
+    removed: &mut [bool],
+    added: &mut [bool],
+) {
+    let prefix = common_prefix(&a[a_range.clone()], &b[b_range.clone()]);
+    a_range.start += prefix;
+    b_range.start += prefix;
+    let suffix = common_suffix(&a[a_range.clone()], &b[b_range.clone()]);
+    a_range.end -= suffix;
+    b_range.end -= suffix;
+
+    if a_range.is_empty() {
+        added[b_range].fill(true);
+    } else if b_range.is_empty() {
+        removed[a_range].fill(true);
+    } else if let Some((x, y)) =
+        middle_snake(a, a_range.clone(), b, b_range.clone(), forward, backward)
+    {
+        let (a_tail, b_tail) = (x..a_range.end, y..b_range.end);
+        conquer(
+            a,
+            a_range.start..x,
+            b,
+            b_range.start..y,
+            forward,
+            backward,
+            removed,
+            added,
+        );
+        conquer(a, a_tail, b, b_tail, forward, backward, removed, added);
+    } else {
+        removed[a_range].fill(true);
+        added[b_range].fill(true);
+    }
+}
+
+/// A point on an optimal edit path through the middle of the ranges, found
+/// by searching from both ends at once
+fn middle_snake(
+    a: &[usize],
+    a_range: Range<usize>,
+    b: &[usize],
+    b_range: Range<usize>,
+    forward: &mut Frontier,
+    backward: &mut Frontier,
+) -> Option<(usize, usize)> {
+    let n = a_range.len();
+    let m = b_range.len();
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    forward[1] = 0;
+    backward[1] = 0;
+    let max = ((n + m + 1) / 2 + 1) as isize;
+    for d in 0..max {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += common_prefix(
+                    &a[a_range.start + x..a_range.end],
+                    &b[b_range.start + y..b_range.end],
+                );
+            }
+            forward[k] = x;
+            if odd && (k - delta).abs() < d && forward[k] + backward[-(k - delta)] >= n {
+                return Some((x0 + a_range.start, y0 + b_range.start));
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = common_suffix(
+                    &a[a_range.start..a_range.start + n - x],
+                    &b[b_range.start..b_range.start + m - y],
+                );
+                x += advance;
+                y += advance;
+            }
+            backward[k] = x;
+            if !odd && (k - delta).abs() <= d && backward[k] + forward[-(k - delta)] >= n {
+                return Some((n - x + a_range.start, m - y + b_range.start));
+            }
+        }
+    }
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+
+    fn diff(args: &[&str], cwd: &Path) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, cwd, &mut io::empty());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn finds_minimal_changes() {
+        let a = [1, 2, 3, 4, 5, 6];
+        let b = [1, 3, 4, 7, 6, 8];
+        assert_eq!(
+            changes(&a, &b),
+            vec![
+                Change { a: 1..2, b: 1..1 },
+                Change { a: 4..5, b: 3..4 },
+                Change { a: 6..6, b: 5..6 },
+            ]
+        );
+        assert!(changes(&a, &a).is_empty());
+        assert_eq!(changes(&[], &[1]), vec![Change { a: 0..0, b: 0..1 }]);
+    }
+
+    #[test]
+    fn prints_normal_and_unified_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "one\ntwo\nthree\nfour\n").unwrap();
+        fs::write(dir.path().join("b"), "one\nTWO\nthree\nfour\nfive").unwrap();
+
+        let (out, status) = diff(&["a", "b"], dir.path());
+        assert_eq!(status, 1);
+        assert_eq!(
+            out,
+            "2c2\n< two\n---\n> TWO\n4a5\n> five\n\\ No newline at end of file\n"
+        );
+
+        let (out, _) = diff(
+            &["-U1", "--label", "old", "--label", "new", "a", "b"],
+            dir.path(),
+        );
+        assert_eq!(
+            out,
+            "--- old\n+++ new\n@@ -1,4 +1,5 @@\n one\n-two\n+TWO\n three\n four\n\
+             +five\n\\ No newline at end of file\n"
+        );
+
+        let (out, status) = diff(&["-i", "-q", "a", "a"], dir.path());
+        assert_eq!((out.as_str(), status), ("", 0));
+    }
+
+    #[test]
+    fn prints_context_format() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "x\ny\nz\n").unwrap();
+        fs::write(dir.path().join("b"), "x\nz\n").unwrap();
+        let (out, _) = diff(&["-C0", "--label=a", "--label=b", "a", "b"], dir.path());
+        assert_eq!(
+            out,
+            "*** a\n--- b\n***************\n*** 2 ****\n- y\n--- 1 ----\n"
+        );
+    }
 
     #[test]
-    fn diff_no_panic() {
-        let a = b"foo\nbar\n";
-        let b = b"foo\nbaz\n";
-        // Ensure diff runs without error.
-        diff_streams(BufReader::new(Cursor::new(a)), BufReader::new(Cursor::new(b))).unwrap();
+    fn ignores_white_space_and_case() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "a  b\nC\n").unwrap();
+        fs::write(dir.path().join("b"), "a b \nc\n").unwrap();
+        assert_eq!(diff(&["-b", "a", "b"], dir.path()).1, 1);
+        assert_eq!(diff(&["-bi", "a", "b"], dir.path()), (String::new(), 0));
+        assert_eq!(diff(&["-wi", "a", "b"], dir.path()), (String::new(), 0));
     }
-} 
 
+    #[test]
+    fn rejects_bad_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(diff(&["a"], dir.path()).1, 2);
+        assert_eq!(diff(&["-Z", "a", "b"], dir.path()).1, 2);
+        assert_eq!(diff(&["a", "b"], dir.path()).1, 2);
+    }
+}
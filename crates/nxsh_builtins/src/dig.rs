@@ -1,368 +1,445 @@
-//! `dig` builtin - DNS lookup utility for detailed DNS queries.
+//! `dig` builtin - query DNS name servers
 //!
-//! Delegates to the system `dig` binary when available to provide complete
-//! DNS functionality. When the binary is unavailable, falls back to an internal
-//! implementation using hickory_resolver for common DNS queries.
+//! Syntax:
+//!   dig [@SERVER] [-p PORT] [-t TYPE] [-x ADDRESS] [-46] [+short] [+json]
+//!       [+tcp] [+time=SECONDS] [+tries=N] [NAME] [TYPE]
+//!
+//! Asks for the TYPE records of NAME, A by default, from the name servers
+//! in the system's resolver configuration, or from SERVER, an address or
+//! a host name, when it is given. The servers are tried in turn until one
+//! answers. Without a NAME the root's NS records are asked for. `-x` asks
+//! for the PTR record of ADDRESS. The types known by name are A, AAAA, MX,
+//! TXT, CNAME, NS, SRV, SOA, PTR and ANY; others are given as `TYPE` and
+//! their number.
+//!
+//! The query is sent over UDP and again over TCP when the answer is
+//! truncated; `+tcp` (or `+vc`) uses TCP from the start. `+time` sets how
+//! long to wait for each answer, 5 seconds by default, and `+tries` how
+//! many times a UDP query is sent, 3 by default. `-4` and `-6` use only
+//! servers of that address family.
+//!
+//! The answer is printed in sections as `dig` does; `+short` prints only
+//! the data of the answer's records and `+json` the whole answer as a JSON
+//! object. In a structured pipeline `dig` gives a table of the answer's
+//! records with the columns name, type, ttl and data:
+//!
+//!   dig example.com MX | sort-by data | select data
+//!
+//! The client is built in, so no resolver library or external `dig` is
+//! needed.
+//!
+//! The exit status is 0 when a server answered, whatever its status, 1 for
+//! invalid arguments and 9 when no server could be reached.
 
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use std::net::IpAddr;
-use which::which;
-use trust_dns_resolver::{Resolver, config::{ResolverConfig, ResolverOpts}};
-use trust_dns_resolver::proto::rr::RecordType;
+use crate::common::dns::{self, QueryOptions, Record, RecordType, Reply};
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
-pub struct DigOptions {
-    domain: String,
-    record_type: RecordType,
-    server: Option<String>,
-    port: Option<u16>,
-    verbose: bool,
-    short: bool,
-    reverse: bool,
-    use_internal: bool,
-}
+/// The `dig` builtin command implementation
+pub struct DigCommand;
 
-impl Default for DigOptions {
-    fn default() -> Self {
-        Self {
-            domain: String::new(),
-            record_type: RecordType::A,
-            server: None,
-            port: Some(53),
-            verbose: false,
-            short: false,
-            reverse: false,
-            use_internal: false,
-        }
+impl Builtin for DigCommand {
+    fn name(&self) -> &'static str {
+        "dig"
     }
-}
 
-/// Entry point for the `dig` builtin.
-pub fn dig_cli(args: &[String]) -> Result<()> {
-    let options = parse_dig_args(args)?;
-    
-    // Prefer the full-featured system implementation when present (unless forced internal).
-    if !options.use_internal {
-        if let Ok(result) = try_external_dig(args) {
-            return result;
-        }
-        
-        if options.verbose {
-            println!("; dig: external binary not found, using internal implementation");
-        }
+    fn synopsis(&self) -> &'static str {
+        "Query DNS name servers"
     }
-    
-    // Use internal implementation
-    run_internal_dig(&options)
-}
 
-fn try_external_dig(args: &[String]) -> Result<Result<()>> {
-    if let Ok(path) = which("dig") {
-        let status = Command::new(path)
-            .args(args)
-            .status()
-            .map_err(|e| anyhow!("dig: failed to launch backend: {e}"))?;
-        std::process::exit(status.code().unwrap_or(1));
+    fn description(&self) -> &'static str {
+        "Look up A, AAAA, MX, TXT, CNAME, NS, SRV and other records from the system's \
+         name servers or a given one, printed in full, short or as JSON."
     }
-    
-    Err(anyhow!("dig: backend not found"))
-}
 
-fn parse_dig_args(args: &[String]) -> Result<DigOptions> {
-    let mut options = DigOptions::default();
-    let mut i = 0;
-    
-    while i < args.len() {
-        match args[i].as_str() {
-            "-h" | "--help" => {
-                print_dig_help();
-                std::process::exit(0);
-            }
-            "-v" | "--verbose" => {
-                options.verbose = true;
-            }
-            "+short" => {
-                options.short = true;
-            }
-            "-x" => {
-                options.reverse = true;
-            }
-            "--internal" => {
-                options.use_internal = true;
-            }
-            "-p" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("dig: -p requires a port number"));
-                }
-                options.port = Some(args[i].parse()
-                    .map_err(|_| anyhow!("dig: invalid port: {}", args[i]))?);
-            }
-            arg if arg.starts_with('@') => {
-                options.server = Some(arg[1..].to_string());
-            }
-            arg if !arg.starts_with('-') && !arg.starts_with('+') => {
-                if options.domain.is_empty() {
-                    options.domain = arg.to_string();
-                } else {
-                    // Check if it's a record type
-                    match arg.to_uppercase().as_str() {
-                        "A" => options.record_type = RecordType::A,
-                        "AAAA" => options.record_type = RecordType::AAAA,
-                        "CNAME" => options.record_type = RecordType::CNAME,
-                        "MX" => options.record_type = RecordType::MX,
-                        "NS" => options.record_type = RecordType::NS,
-                        "PTR" => options.record_type = RecordType::PTR,
-                        "SOA" => options.record_type = RecordType::SOA,
-                        "TXT" => options.record_type = RecordType::TXT,
-                        "SRV" => options.record_type = RecordType::SRV,
-                        "ANY" => options.record_type = RecordType::ANY,
-                        _ => return Err(anyhow!("dig: unknown record type or too many domains: {}", arg)),
-                    }
-                }
-            }
-            _ => {
-                return Err(anyhow!("dig: unknown option: {}", args[i]));
-            }
-        }
-        i += 1;
+    fn usage(&self) -> &'static str {
+        "dig [@SERVER] [-p PORT] [-t TYPE] [-x ADDRESS] [-46] [+short] [+json] [+tcp] \
+         [+time=SECONDS] [+tries=N] [NAME] [TYPE]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Query DNS name servers. Use 'dig example.com MX +short' for the mail servers \
+         of a domain or 'dig @1.1.1.1 -x 192.0.2.1' for the name of an address."
     }
-    
-    if options.domain.is_empty() {
-        return Err(anyhow!("dig: no domain specified"));
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
     }
-    
-    Ok(options)
 }
 
-fn print_dig_help() {
-    println!("Usage: dig [@server] [domain] [type] [options]");
-    println!();
-    println!("Options:");
-    println!("  -h, --help        Show this help message");
-    println!("  -v, --verbose     Enable verbose output");
-    println!("  -x                Reverse lookup (PTR record)");
-    println!("  -p PORT           Use specific port (default: 53)");
-    println!("  @SERVER           Use specific DNS server");
-    println!("  +short            Short answer format");
-    println!("  --internal        Force use of internal implementation");
-    println!();
-    println!("Record Types:");
-    println!("  A, AAAA, CNAME, MX, NS, PTR, SOA, TXT, SRV, ANY");
-    println!();
-    println!("Examples:");
-    println!("  dig example.com");
-    println!("  dig example.com MX");
-    println!("  dig @8.8.8.8 example.com");
-    println!("  dig +short example.com");
-    println!("  dig -x 8.8.8.8");
+/// Run dig for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
 }
 
-fn run_internal_dig(options: &DigOptions) -> Result<()> {
-    let resolver = if let Some(server) = &options.server {
-        // Use custom DNS server
-        let server_addr: IpAddr = server.parse()
-            .map_err(|_| anyhow!("dig: invalid DNS server address: {}", server))?;
-        
-        let config = ResolverConfig::from_parts(
-            None,
-            vec![],
-            vec![(trust_dns_resolver::config::NameServerConfig {
-                socket_addr: std::net::SocketAddr::new(server_addr, options.port.unwrap_or(53)),
-                protocol: trust_dns_resolver::config::Protocol::Udp,
-                tls_dns_name: None,
-                trust_negative_responses: false,
-                bind_addr: None,
-            })],
-        );
-        Resolver::new(config, ResolverOpts::default())
-    } else {
-        // Use system resolver
-        Resolver::from_system_conf()
-    }.map_err(|e| anyhow!("dig: failed to create resolver: {}", e))?;
-    
-    if !options.short {
-        // Print header similar to dig
-            println!("; <<>> dig 1.0 (NexusShell internal) <<>> {} {}", 
-                options.domain, format_record_type(options.record_type));
-        println!(";; global options: +cmd");
+/// The answer records for the query `args` describe, for the structured
+/// `dig`
+pub(crate) fn answer_records(args: &[String]) -> Result<Vec<Record>, String> {
+    let options = Options::parse(args)?;
+    let reply = options.ask().map_err(|failure| failure.message)?;
+    Ok(reply.response.answers)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+/// Why no answer was printed
+struct Failure {
+    message: String,
+    status: i32,
+}
+
+impl Failure {
+    fn usage(message: String) -> Self {
+        Failure { message, status: 1 }
     }
-    
-    let result = if options.reverse {
-        // Reverse lookup
-        let addr: IpAddr = options.domain.parse()
-            .map_err(|_| anyhow!("dig: invalid IP address for reverse lookup: {}", options.domain))?;
-        
-        match resolver.reverse_lookup(addr) {
-            Ok(response) => {
-                if options.short {
-                    for name in response.iter() {
-                        println!("{name}");
-                    }
-                } else {
-                    print_reverse_response(&options.domain, &response);
-                }
-                Ok(())
-            }
-            Err(e) => Err(anyhow!("dig: reverse lookup failed: {}", e)),
-        }
-    } else {
-        // Forward lookup
-        match options.record_type {
-            RecordType::A => {
-                match resolver.lookup_ip(&options.domain) {
-                    Ok(response) => {
-                        if options.short {
-                            for ip in response.iter() {
-                                if ip.is_ipv4() {
-                                    println!("{ip}");
-                                }
-                            }
-                        } else {
-                            print_lookup_response(&options.domain, "A", &response.iter().filter(|ip| ip.is_ipv4()).collect::<Vec<_>>());
-                        }
-                        Ok(())
-                    }
-                    Err(e) => Err(anyhow!("dig: A record lookup failed: {}", e)),
-                }
-            }
-            RecordType::AAAA => {
-                match resolver.lookup_ip(&options.domain) {
-                    Ok(response) => {
-                        if options.short {
-                            for ip in response.iter() {
-                                if ip.is_ipv6() {
-                                    println!("{ip}");
-                                }
+}
+
+fn run(args: &[String]) -> Outcome {
+    let answer = Options::parse(args)
+        .map_err(Failure::usage)
+        .and_then(|options| {
+            let reply = options.ask()?;
+            Ok(if options.json {
+                json_answer(&reply)
+            } else if options.short {
+                short_answer(&reply)
+            } else {
+                full_answer(&reply, args)
+            })
+        });
+    match answer {
+        Ok(stdout) => Outcome {
+            stdout,
+            stderr: String::new(),
+            status: 0,
+        },
+        Err(failure) => Outcome {
+            stdout: String::new(),
+            stderr: format!("dig: {}\n", failure.message),
+            status: failure.status,
+        },
+    }
+}
+
+/// Parsed command line
+#[derive(Debug)]
+struct Options {
+    name: Option<String>,
+    rtype: Option<RecordType>,
+    server: Option<String>,
+    port: u16,
+    /// `-4` or `-6`
+    family: Option<u8>,
+    short: bool,
+    json: bool,
+    query: QueryOptions,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            name: None,
+            rtype: None,
+            server: None,
+            port: 53,
+            family: None,
+            short: false,
+            json: false,
+            query: QueryOptions::default(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(server) = arg.strip_prefix('@') {
+                options.server = Some(server.to_string());
+            } else if let Some(setting) = arg.strip_prefix('+') {
+                options.set(setting)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                let flag = arg[1..].chars().next().unwrap_or_default();
+                match flag {
+                    '4' | '6' if arg.len() == 2 => options.family = Some(flag as u8 - b'0'),
+                    'p' | 't' | 'x' => {
+                        let value = match &arg[2..] {
+                            "" => iter.next().cloned().ok_or_else(|| {
+                                format!("option requires an argument -- '{flag}'")
+                            })?,
+                            attached => attached.to_string(),
+                        };
+                        match flag {
+                            'p' => {
+                                options.port = value
+                                    .parse()
+                                    .map_err(|_| format!("invalid port '{value}'"))?
                             }
-                        } else {
-                            print_lookup_response(&options.domain, "AAAA", &response.iter().filter(|ip| ip.is_ipv6()).collect::<Vec<_>>());
-                        }
-                        Ok(())
-                    }
-                    Err(e) => Err(anyhow!("dig: AAAA record lookup failed: {}", e)),
-                }
-            }
-            RecordType::MX => {
-                match resolver.mx_lookup(&options.domain) {
-                    Ok(response) => {
-                        if options.short {
-                            for mx in response.iter() {
-                                println!("{} {}", mx.preference(), mx.exchange());
+                            't' => {
+                                options.rtype = Some(
+                                    RecordType::from_name(&value)
+                                        .ok_or_else(|| format!("invalid type '{value}'"))?,
+                                )
                             }
-                        } else {
-                            let mx_records: Vec<String> = response.iter()
-                                .map(|mx| format!("{} {}", mx.preference(), mx.exchange()))
-                                .collect();
-                            print_lookup_response(&options.domain, "MX", &mx_records);
-                        }
-                        Ok(())
-                    }
-                    Err(e) => Err(anyhow!("dig: MX record lookup failed: {}", e)),
-                }
-            }
-            RecordType::TXT => {
-                match resolver.txt_lookup(&options.domain) {
-                    Ok(response) => {
-                        if options.short {
-                            for txt in response.iter() {
-                                for data in txt.iter() {
-                                    println!("{}", String::from_utf8_lossy(data));
-                                }
+                            _ => {
+                                let address: IpAddr = value
+                                    .parse()
+                                    .map_err(|_| format!("invalid address '{value}'"))?;
+                                options.name = Some(dns::reverse_name(address));
+                                options.rtype.get_or_insert(RecordType::PTR);
                             }
-                        } else {
-                            let txt_records: Vec<String> = response.iter()
-                                .flat_map(|txt| txt.iter())
-                                .map(|data| format!("\"{}\"", String::from_utf8_lossy(data)))
-                                .collect();
-                            print_lookup_response(&options.domain, "TXT", &txt_records);
                         }
-                        Ok(())
                     }
-                    Err(e) => Err(anyhow!("dig: TXT record lookup failed: {}", e)),
+                    _ => return Err(format!("invalid option '{arg}'")),
                 }
-            }
-            _ => {
-                Err(anyhow!("dig: record type {} not supported in internal implementation", format_record_type(options.record_type)))
+            } else if let (None, Some(rtype)) = (options.rtype, RecordType::from_name(arg)) {
+                options.rtype = Some(rtype);
+            } else if options.name.is_none() {
+                options.name = Some(arg.clone());
+            } else {
+                return Err(format!("unexpected argument '{arg}'"));
             }
         }
-    };
-    
-    if !options.short && result.is_ok() {
-        println!();
-        println!(";; Query time: 0 msec");
-        if let Some(server) = &options.server {
-            println!(";; SERVER: {}#{}", server, options.port.unwrap_or(53));
-        } else {
-            println!(";; SERVER: system resolver");
+        Ok(options)
+    }
+
+    /// Take a `+` setting
+    fn set(&mut self, setting: &str) -> Result<(), String> {
+        let (name, value) = match setting.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (setting, None),
+        };
+        let number = |value: Option<&str>| {
+            value
+                .and_then(|value| value.parse::<u32>().ok())
+                .ok_or_else(|| format!("invalid option '+{setting}'"))
+        };
+        match name {
+            "short" => self.short = true,
+            "noshort" => self.short = false,
+            "json" => self.json = true,
+            "nojson" => self.json = false,
+            "tcp" | "vc" => self.query.tcp = true,
+            "notcp" | "novc" => self.query.tcp = false,
+            "time" => self.query.timeout = Duration::from_secs(number(value)?.max(1).into()),
+            "tries" => self.query.tries = number(value)?.max(1),
+            "retry" => self.query.tries = number(value)? + 1,
+            _ => return Err(format!("invalid option '+{setting}'")),
         }
-        println!(";; WHEN: {}", chrono::Local::now().format("%a %b %d %H:%M:%S %Z %Y"));
+        Ok(())
     }
-    
-    result
-}
 
-fn format_record_type(record_type: RecordType) -> &'static str {
-    match record_type {
-        RecordType::A => "A",
-        RecordType::AAAA => "AAAA",
-        RecordType::CNAME => "CNAME",
-        RecordType::MX => "MX",
-        RecordType::NS => "NS",
-        RecordType::PTR => "PTR",
-        RecordType::SOA => "SOA",
-        RecordType::TXT => "TXT",
-        RecordType::SRV => "SRV",
-        RecordType::ANY => "ANY",
-        _ => "UNKNOWN",
+    /// The query's name and type
+    fn question(&self) -> (&str, RecordType) {
+        match &self.name {
+            Some(name) => (name, self.rtype.unwrap_or(RecordType::A)),
+            None => (".", self.rtype.unwrap_or(RecordType::NS)),
+        }
+    }
+
+    /// The addresses to ask, in order
+    fn servers(&self) -> Result<Vec<SocketAddr>, Failure> {
+        let hosts = match &self.server {
+            Some(server) => match server.parse::<IpAddr>() {
+                Ok(address) => vec![address],
+                Err(_) => (server.as_str(), self.port)
+                    .to_socket_addrs()
+                    .map_err(|e| {
+                        Failure::usage(format!("couldn't get address for '{server}': {e}"))
+                    })?
+                    .map(|address| address.ip())
+                    .collect(),
+            },
+            None => dns::system_servers(),
+        };
+        let servers: Vec<SocketAddr> = hosts
+            .into_iter()
+            .filter(|host| match self.family {
+                Some(4) => host.is_ipv4(),
+                Some(_) => host.is_ipv6(),
+                None => true,
+            })
+            .map(|host| SocketAddr::new(host, self.port))
+            .collect();
+        if servers.is_empty() {
+            return Err(Failure {
+                message: "no name servers to ask".to_string(),
+                status: 9,
+            });
+        }
+        Ok(servers)
     }
-}
 
-fn print_lookup_response<T: std::fmt::Display>(domain: &str, record_type: &str, records: &[T]) {
-    println!(";; Got answer:");
-    println!(";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1");
-    println!(";; flags: qr rd ra; QUERY: 1, ANSWER: {}, AUTHORITY: 0, ADDITIONAL: 0", records.len());
-    println!();
-    println!(";; QUESTION SECTION:");
-    println!(";{domain}\t\tIN\t{record_type}");
-    println!();
-    println!(";; ANSWER SECTION:");
-    
-    for record in records {
-    println!("{domain}\t300\tIN\t{record_type}\t{record}");
+    /// Ask each server in turn until one answers
+    fn ask(&self) -> Result<Reply, Failure> {
+        let (name, rtype) = self.question();
+        dns::encode_query(0, name, rtype).map_err(Failure::usage)?;
+        let mut errors = Vec::new();
+        for server in self.servers()? {
+            match dns::query(server, name, rtype, &self.query) {
+                Ok(reply) => return Ok(reply),
+                Err(e) => errors.push(format!("{server}: {e}")),
+            }
+        }
+        Err(Failure {
+            message: format!("no answer ({})", errors.join("; ")),
+            status: 9,
+        })
     }
 }
 
-fn print_reverse_response(ip: &str, response: &trust_dns_resolver::lookup::ReverseLookup) {
-    println!(";; Got answer:");
-    println!(";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1");
-    println!(";; flags: qr rd ra; QUERY: 1, ANSWER: {}, AUTHORITY: 0, ADDITIONAL: 0", response.iter().count());
-    println!();
-    println!(";; QUESTION SECTION:");
-    println!(";{ip}.in-addr.arpa.\t\tIN\tPTR");
-    println!();
-    println!(";; ANSWER SECTION:");
-    
-    for name in response.iter() {
-    println!("{ip}.in-addr.arpa.\t300\tIN\tPTR\t{name}");
+fn full_answer(reply: &Reply, args: &[String]) -> String {
+    let response = &reply.response;
+    let mut out = String::new();
+    let _ = writeln!(out, "; <<>> nxsh dig <<>> {}", args.join(" "));
+    let _ = writeln!(out, ";; Got answer:");
+    let _ = writeln!(
+        out,
+        ";; ->>HEADER<<- opcode: QUERY, status: {}, id: {}",
+        response.status(),
+        response.id
+    );
+    let _ = writeln!(
+        out,
+        ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+        response.flag_names().join(" "),
+        response.questions.len(),
+        response.answers.len(),
+        response.authority.len(),
+        response.additional.len()
+    );
+    out.push_str("\n;; QUESTION SECTION:\n");
+    for question in &response.questions {
+        let _ = writeln!(out, ";{}\t\tIN\t{}", question.name, question.rtype);
+    }
+    for (title, records) in [
+        ("ANSWER", &response.answers),
+        ("AUTHORITY", &response.authority),
+        ("ADDITIONAL", &response.additional),
+    ] {
+        if records.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "\n;; {title} SECTION:");
+        for record in records {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                record.name,
+                record.ttl,
+                record.class_name(),
+                record.rtype,
+                record.data
+            );
+        }
     }
+    let _ = writeln!(out, "\n;; Query time: {} msec", reply.elapsed.as_millis());
+    let _ = writeln!(
+        out,
+        ";; SERVER: {}#{}({}) ({})",
+        reply.server.ip(),
+        reply.server.port(),
+        reply.server.ip(),
+        if reply.tcp { "TCP" } else { "UDP" }
+    );
+    let _ = writeln!(out, ";; MSG SIZE  rcvd: {}", reply.size);
+    out
+}
+
+fn short_answer(reply: &Reply) -> String {
+    reply
+        .response
+        .answers
+        .iter()
+        .map(|record| format!("{}\n", record.data))
+        .collect()
+}
+
+fn json_answer(reply: &Reply) -> String {
+    let records = |records: &[Record]| -> Value {
+        records
+            .iter()
+            .map(|record| {
+                json!({
+                    "name": record.name,
+                    "type": record.rtype.to_string(),
+                    "class": record.class_name(),
+                    "ttl": record.ttl,
+                    "data": record.data.to_string(),
+                })
+            })
+            .collect()
+    };
+    let response = &reply.response;
+    let questions: Value = response
+        .questions
+        .iter()
+        .map(|question| json!({"name": question.name, "type": question.rtype.to_string()}))
+        .collect();
+    let answer = json!({
+        "id": response.id,
+        "status": response.status(),
+        "flags": response.flag_names(),
+        "question": questions,
+        "answer": records(&response.answers),
+        "authority": records(&response.authority),
+        "additional": records(&response.additional),
+        "server": reply.server.to_string(),
+        "protocol": if reply.tcp { "tcp" } else { "udp" },
+        "query_time_ms": reply.elapsed.as_millis() as u64,
+        "size": reply.size,
+    });
+    serde_json::to_string_pretty(&answer).unwrap_or_default() + "\n"
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
     #[test]
-    fn test_parse_dig_args() {
-        let args = vec!["example.com".to_string()];
-        let options = parse_dig_args(&args).unwrap();
-        assert_eq!(options.domain, "example.com");
-        assert_eq!(options.record_type, RecordType::A);
-        
-        let args = vec!["example.com".to_string(), "MX".to_string()];
-        let options = parse_dig_args(&args).unwrap();
-        assert_eq!(options.record_type, RecordType::MX);
+    fn parses_servers_types_and_settings() {
+        let parsed = options(&["@192.0.2.53", "mx", "example.com", "+short", "+time=2"]).unwrap();
+        assert_eq!(parsed.server.as_deref(), Some("192.0.2.53"));
+        assert_eq!(parsed.question(), ("example.com", RecordType::MX));
+        assert!(parsed.short && !parsed.json);
+        assert_eq!(parsed.query.timeout, Duration::from_secs(2));
+
+        let parsed = options(&["-x", "192.0.2.1", "-p5353", "+tcp", "+retry=0"]).unwrap();
+        assert_eq!(
+            parsed.question(),
+            ("1.2.0.192.in-addr.arpa.", RecordType::PTR)
+        );
+        assert_eq!(parsed.port, 5353);
+        assert!(parsed.query.tcp);
+        assert_eq!(parsed.query.tries, 1);
+
+        assert_eq!(options(&[]).unwrap().question(), (".", RecordType::NS));
+        assert_eq!(
+            options(&["-t", "BOGUS"]).unwrap_err(),
+            "invalid type 'BOGUS'"
+        );
+        assert_eq!(options(&["+nope"]).unwrap_err(), "invalid option '+nope'");
+        assert_eq!(
+            options(&["a.example", "b.example"]).unwrap_err(),
+            "unexpected argument 'b.example'"
+        );
     }
 }
-
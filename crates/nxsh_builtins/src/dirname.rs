@@ -0,0 +1,143 @@
+//! `dirname` builtin - strip the last component from names
+//!
+//! Syntax:
+//!   dirname [-z] NAME...
+//!
+//! Prints each NAME without its last component and the separators before
+//! it: `.` when NAME has no directory part and `/` when only the root is
+//! left. `-z` ends each line with NUL instead of a newline. Names are taken
+//! as text; the file system is not consulted.
+//!
+//! The exit status is 0 on success and 1 on a usage error.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
+use std::path::is_separator;
+
+/// The `dirname` builtin command implementation
+pub struct DirnameCommand;
+
+impl Builtin for DirnameCommand {
+    fn name(&self) -> &'static str {
+        "dirname"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Strip the last component from names"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print each NAME with its last component removed, or '.' when it has no \
+         directory part."
+    }
+
+    fn usage(&self) -> &'static str {
+        "dirname [-z] NAME..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Strip the last component from names. Use 'dirname src/main.rs' to get 'src'."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run dirname for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// NAME without its last component
+fn parent(name: &str) -> &str {
+    let trimmed = name.trim_end_matches(is_separator);
+    if trimmed.is_empty() {
+        return if name.is_empty() { "." } else { &name[..1] };
+    }
+    match trimmed.rfind(is_separator) {
+        Some(at) => match trimmed[..at].trim_end_matches(is_separator) {
+            "" => &trimmed[..1],
+            head => head,
+        },
+        None => ".",
+    }
+}
+
+fn run(args: &[String]) -> Outcome {
+    let usage = |message: String| Outcome {
+        stdout: Vec::new(),
+        stderr: format!("dirname: {message}\n").into_bytes(),
+        status: 1,
+    };
+    let mut terminator = b'\n';
+    let mut names = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                names.extend(iter.by_ref().cloned());
+                break;
+            }
+            "-z" | "--zero" => terminator = b'\0',
+            option if option.len() > 1 && option.starts_with('-') => {
+                return usage(match option.strip_prefix("--") {
+                    Some(_) => format!("unrecognized option '{option}'"),
+                    None => format!(
+                        "invalid option -- '{}'",
+                        option.chars().nth(1).unwrap_or('-')
+                    ),
+                });
+            }
+            _ => names.push(arg.clone()),
+        }
+    }
+    if names.is_empty() {
+        return usage("missing operand".to_string());
+    }
+    let mut stdout = Vec::new();
+    for name in &names {
+        stdout.extend_from_slice(parent(name).as_bytes());
+        stdout.push(terminator);
+    }
+    Outcome {
+        stdout,
+        stderr: Vec::new(),
+        status: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_last_component() {
+        assert_eq!(parent("/usr/lib/"), "/usr");
+        assert_eq!(parent("dir//file"), "dir");
+        assert_eq!(parent("/file"), "/");
+        assert_eq!(parent("file"), ".");
+        assert_eq!(parent("//"), "/");
+        assert_eq!(parent(""), ".");
+
+        let args = vec!["-z".to_string(), "a/b".to_string(), "c".to_string()];
+        assert_eq!(run(&args).stdout, b"a\0.\0");
+        assert_eq!(run(&[]).status, 1);
+    }
+}
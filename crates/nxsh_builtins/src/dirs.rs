@@ -1,55 +1,559 @@
-//! `dirs` builtin  Edisplay contents of directory stack.
-//! Currently supports basic usage: `dirs` prints stack from most-recent to oldest.
-//! Options `-c` (clear), `-l` (long path) are stubbed for future expansion.
+//! `dirs`, `pushd` and `popd` builtins - the directory stack
+//!
+//! Syntax:
+//!   dirs [-clpv] [+N | -N]
+//!   pushd [-n] [DIR | +N | -N]
+//!   popd [-n] [+N | -N]
+//!
+//! Entry 0 of the stack is the working directory, followed by the
+//! directories pushd saved, most recent first. `pushd DIR` changes to DIR
+//! the way `cd` does, CDPATH and `-` included, and saves the directory it
+//! left; with no argument it swaps the first two entries. `pushd +N`
+//! rotates the stack so that entry N, counted from the left, is on top and
+//! changes to it; `-N` counts from the right. `popd` drops entry 0 and
+//! changes to the next one, `popd +N` and `popd -N` drop entry N. With `-n`,
+//! `pushd DIR` saves DIR as entry 1 and `popd` drops entry 1, and the
+//! working directory stays as it is.
+//!
+//! pushd and popd print the stack when they succeed. dirs prints it on one
+//! line with the home directory as `~`, as the prompt shows it; `-l` prints
+//! full paths, `-p` one entry per line and `-v` one per line with its
+//! number. `+N` and `-N` print only that entry, and `-c` empties the stack.
+//!
+//! The stack is part of the shell's state, so it lasts from one command
+//! line to the next; a subshell works on a copy.
+//!
+//! The exit status is 0 on success, 1 when the stack has no such entry or
+//! a directory cannot be entered, and 2 for bad usage.
 
-use anyhow::Result;
-use once_cell::sync::Lazy;
-use std::env;
+use crate::cd::CdCommand;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 use std::path::PathBuf;
-use std::sync::Mutex;
 
-pub static DIR_STACK: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(|| {
-    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
-    Mutex::new(vec![cwd])
-});
+/// The `dirs` builtin command implementation
+pub struct DirsCommand;
 
-pub fn pushd(dir: PathBuf) {
-    let mut stack = DIR_STACK.lock().unwrap();
-    stack.push(dir);
+/// The `pushd` builtin command implementation
+pub struct PushdCommand;
+
+/// The `popd` builtin command implementation
+pub struct PopdCommand;
+
+impl Builtin for DirsCommand {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Display the directory stack"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the directory stack kept by pushd and popd, the working directory \
+         first. -v numbers the entries, -l shows full paths and -c clears the stack."
+    }
+
+    fn usage(&self) -> &'static str {
+        "dirs [-clpv] [+N | -N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the directory stack. Use 'dirs -v' to see the numbers 'pushd +N' \
+         rotates to or 'dirs -c' to forget every saved directory."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Dirs, args)
+    }
 }
 
-pub fn popd() -> Option<PathBuf> {
-    let mut stack = DIR_STACK.lock().unwrap();
-    if stack.len() > 1 { stack.pop() } else { None }
+impl Builtin for PushdCommand {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Save the directory and change to another"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change to DIR, saving the current directory on the directory stack, swap \
+         the top two entries when no DIR is given, or rotate entry N to the top."
+    }
+
+    fn usage(&self) -> &'static str {
+        "pushd [-n] [DIR | +N | -N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Change directory and remember the old one. Use 'pushd /etc' to go there, \
+         'popd' to come back and a bare 'pushd' to flip between the two."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Pushd, args)
+    }
 }
 
-/// Entry function for `dirs` builtin.
-pub fn dirs_cli(args: &[String]) -> Result<()> {
-    if args.first().map(|s| s.as_str()) == Some("-c") {
-        // Clear stack except current dir.
-        let cwd = env::current_dir()?;
-        let mut stack = DIR_STACK.lock().unwrap();
-        stack.clear();
-        stack.push(cwd);
-        return Ok(());
+impl Builtin for PopdCommand {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Return to the last saved directory"
     }
-    let stack = DIR_STACK.lock().unwrap();
-    for (i, path) in stack.iter().rev().enumerate() {
-        if i > 0 {
-            print!(" ");
+
+    fn description(&self) -> &'static str {
+        "Remove the top entry of the directory stack and change to the next, or \
+         remove entry N."
+    }
+
+    fn usage(&self) -> &'static str {
+        "popd [-n] [+N | -N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Go back to the directory pushd saved. Use 'popd +1' to drop a saved \
+         directory without leaving this one."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Popd, args)
+    }
+}
+
+fn execute_in(
+    ctx: &mut ShellContext,
+    flavor: Flavor,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let outcome = match Invocation::parse(flavor, args) {
+        Ok(invocation) => invocation.run(ctx),
+        Err(msg) => Outcome::usage(flavor, &msg),
+    };
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+/// Which name the command was run by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Dirs,
+    Pushd,
+    Popd,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Dirs => "dirs",
+            Flavor::Pushd => "pushd",
+            Flavor::Popd => "popd",
         }
-        print!("{}", path.display());
     }
-    println!();
-    Ok(())
+
+    fn usage(self) -> &'static str {
+        match self {
+            Flavor::Dirs => "dirs [-clpv] [+N | -N]",
+            Flavor::Pushd => "pushd [-n] [DIR | +N | -N]",
+            Flavor::Popd => "popd [-n] [+N | -N]",
+        }
+    }
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn printed(text: String) -> Self {
+        Self {
+            stdout: text.into_bytes(),
+            stderr: Vec::new(),
+            status: 0,
+        }
+    }
+
+    fn error(flavor: Flavor, msg: &str) -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: format!("{}: {msg}\n", flavor.name()).into_bytes(),
+            status: 1,
+        }
+    }
+
+    fn usage(flavor: Flavor, msg: &str) -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: format!(
+                "{name}: {msg}\n{name}: usage: {}\n",
+                flavor.usage(),
+                name = flavor.name()
+            )
+            .into_bytes(),
+            status: 2,
+        }
+    }
+}
+
+/// A stack entry named by `+N` or `-N`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Index {
+    from_right: bool,
+    n: usize,
+}
+
+impl Index {
+    fn parse(word: &str) -> Option<Self> {
+        let (from_right, digits) = match word.as_bytes().first()? {
+            b'+' => (false, &word[1..]),
+            b'-' => (true, &word[1..]),
+            _ => return None,
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        // Too large to name an entry is simply out of range
+        let n = digits.parse().unwrap_or(usize::MAX);
+        Some(Self { from_right, n })
+    }
+
+    /// The position this names in a stack of `len` entries
+    fn position(self, len: usize) -> Option<usize> {
+        if self.n >= len {
+            None
+        } else if self.from_right {
+            Some(len - 1 - self.n)
+        } else {
+            Some(self.n)
+        }
+    }
+}
+
+/// What the operand asks for
+#[derive(Debug, PartialEq)]
+enum Target {
+    None,
+    Index(Index, String),
+    Dir(String),
+}
+
+/// A parsed command line
+#[derive(Debug)]
+struct Invocation {
+    flavor: Flavor,
+    no_cd: bool,
+    clear: bool,
+    long: bool,
+    per_line: bool,
+    numbered: bool,
+    target: Target,
+}
+
+impl Invocation {
+    fn parse(flavor: Flavor, args: &[String]) -> Result<Self, String> {
+        let mut invocation = Self {
+            flavor,
+            no_cd: false,
+            clear: false,
+            long: false,
+            per_line: false,
+            numbered: false,
+            target: Target::None,
+        };
+        let mut operands = Vec::new();
+        let mut options_done = false;
+        for arg in args {
+            if options_done || arg == "-" || Index::parse(arg).is_some() {
+                operands.push(arg);
+                continue;
+            }
+            if arg == "--" {
+                options_done = true;
+                continue;
+            }
+            let Some(flags) = arg.strip_prefix('-') else {
+                operands.push(arg);
+                continue;
+            };
+            for flag in flags.chars() {
+                match (flavor, flag) {
+                    (Flavor::Pushd | Flavor::Popd, 'n') => invocation.no_cd = true,
+                    (Flavor::Dirs, 'c') => invocation.clear = true,
+                    (Flavor::Dirs, 'l') => invocation.long = true,
+                    (Flavor::Dirs, 'p') => invocation.per_line = true,
+                    (Flavor::Dirs, 'v') => {
+                        invocation.per_line = true;
+                        invocation.numbered = true;
+                    }
+                    _ => return Err(format!("-{flag}: invalid option")),
+                }
+            }
+        }
+        match operands.as_slice() {
+            [] => {}
+            [word] => {
+                invocation.target = match Index::parse(word) {
+                    Some(index) => Target::Index(index, word.to_string()),
+                    None if flavor == Flavor::Pushd => Target::Dir(word.to_string()),
+                    None => return Err(format!("{word}: invalid argument")),
+                };
+            }
+            _ => return Err("too many arguments".to_string()),
+        }
+        Ok(invocation)
+    }
+
+    fn run(self, ctx: &mut ShellContext) -> Outcome {
+        let mut entries = entries(ctx);
+        match self.flavor {
+            Flavor::Dirs => {
+                if self.clear {
+                    ctx.set_dirs(Vec::new());
+                    return Outcome::printed(String::new());
+                }
+                match &self.target {
+                    Target::Index(index, word) => match index.position(entries.len()) {
+                        Some(pos) => Outcome::printed(format!("{}\n", self.show(&entries[pos]))),
+                        None => self.out_of_range(word),
+                    },
+                    _ => Outcome::printed(self.listing(&entries)),
+                }
+            }
+            Flavor::Pushd => {
+                match &self.target {
+                    Target::None => {
+                        if entries.len() < 2 {
+                            return Outcome::error(self.flavor, "no other directory");
+                        }
+                        entries.swap(0, 1);
+                    }
+                    Target::Index(index, word) => match index.position(entries.len()) {
+                        Some(pos) => entries.rotate_left(pos),
+                        None => return self.out_of_range(word),
+                    },
+                    Target::Dir(dir) if self.no_cd => entries.insert(1, ctx.cwd.join(dir)),
+                    Target::Dir(dir) => {
+                        if let Err(outcome) = self.enter(ctx, dir) {
+                            return outcome;
+                        }
+                        // The directory left behind becomes entry 1
+                        entries.insert(0, ctx.cwd.clone());
+                    }
+                }
+                if entries[0] != ctx.cwd {
+                    let top = entries[0].to_string_lossy().into_owned();
+                    if let Err(outcome) = self.enter(ctx, &top) {
+                        return outcome;
+                    }
+                }
+                self.finish(ctx, entries)
+            }
+            Flavor::Popd => {
+                if entries.len() < 2 {
+                    return Outcome::error(self.flavor, "directory stack empty");
+                }
+                let pos = match &self.target {
+                    Target::Index(index, word) => match index.position(entries.len()) {
+                        Some(pos) => pos,
+                        None => return self.out_of_range(word),
+                    },
+                    _ => 0,
+                };
+                if pos == 0 && self.no_cd {
+                    entries.remove(1);
+                } else if pos == 0 {
+                    let next = entries[1].to_string_lossy().into_owned();
+                    if let Err(outcome) = self.enter(ctx, &next) {
+                        return outcome;
+                    }
+                    entries.remove(0);
+                } else {
+                    entries.remove(pos);
+                }
+                self.finish(ctx, entries)
+            }
+        }
+    }
+
+    /// Change to `dir` as `cd` would, reporting failures under this name
+    fn enter(&self, ctx: &mut ShellContext, dir: &str) -> Result<(), Outcome> {
+        match CdCommand.change_directory(ctx, dir, false) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = e.message.strip_prefix("cd: ").unwrap_or(&e.message);
+                Err(Outcome::error(self.flavor, msg))
+            }
+        }
+    }
+
+    /// Keep `entries` below the new working directory and print the stack
+    fn finish(&self, ctx: &mut ShellContext, mut entries: Vec<PathBuf>) -> Outcome {
+        entries[0] = ctx.cwd.clone();
+        store(ctx, &entries);
+        Outcome::printed(self.listing(&entries))
+    }
+
+    fn out_of_range(&self, word: &str) -> Outcome {
+        Outcome::error(
+            self.flavor,
+            &format!("{word}: directory stack index out of range"),
+        )
+    }
+
+    fn show(&self, path: &std::path::Path) -> String {
+        if self.long {
+            path.display().to_string()
+        } else {
+            nxsh_ui::prompt::format_cwd(path, None)
+        }
+    }
+
+    fn listing(&self, entries: &[PathBuf]) -> String {
+        let mut out = String::new();
+        for (i, path) in entries.iter().enumerate() {
+            if self.numbered {
+                out.push_str(&format!("{i:2}  {}\n", self.show(path)));
+            } else if self.per_line {
+                out.push_str(&format!("{}\n", self.show(path)));
+            } else {
+                if i > 0 {
+                    out.push(' ');
+                }
+                out.push_str(&self.show(path));
+            }
+        }
+        if !self.per_line {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// The whole stack, the working directory first
+fn entries(ctx: &ShellContext) -> Vec<PathBuf> {
+    let mut entries = vec![ctx.cwd.clone()];
+    entries.extend(ctx.dirs().into_iter().rev());
+    entries
+}
+
+/// Keep all but entry 0, which is always the working directory
+fn store(ctx: &ShellContext, entries: &[PathBuf]) {
+    ctx.set_dirs(entries[1..].iter().rev().cloned().collect());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn parse(flavor: Flavor, list: &[&str]) -> Result<Invocation, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Invocation::parse(flavor, &args)
+    }
+
     #[test]
-    fn print_stack() {
-        dirs_cli(&[]).unwrap();
+    fn parses_indexes_and_options() {
+        assert_eq!(
+            Index::parse("+2"),
+            Some(Index {
+                from_right: false,
+                n: 2
+            })
+        );
+        assert_eq!(
+            Index::parse("-0"),
+            Some(Index {
+                from_right: true,
+                n: 0
+            })
+        );
+        assert_eq!(Index::parse("-n"), None);
+        assert_eq!(Index::parse("+"), None);
+        assert_eq!(Index::parse("+1").unwrap().position(3), Some(1));
+        assert_eq!(Index::parse("-0").unwrap().position(3), Some(2));
+        assert_eq!(Index::parse("+3").unwrap().position(3), None);
+
+        let dirs = parse(Flavor::Dirs, &["-lv"]).unwrap();
+        assert!(dirs.long && dirs.numbered && dirs.per_line);
+        let pushd = parse(Flavor::Pushd, &["-n", "-"]).unwrap();
+        assert!(pushd.no_cd);
+        assert_eq!(pushd.target, Target::Dir("-".to_string()));
+        let popd = parse(Flavor::Popd, &["-1"]).unwrap();
+        assert!(matches!(popd.target, Target::Index(..)));
+
+        assert_eq!(
+            parse(Flavor::Dirs, &["-n"]).unwrap_err(),
+            "-n: invalid option"
+        );
+        assert_eq!(
+            parse(Flavor::Popd, &["src"]).unwrap_err(),
+            "src: invalid argument"
+        );
+        assert_eq!(
+            parse(Flavor::Pushd, &["a", "b"]).unwrap_err(),
+            "too many arguments"
+        );
     }
-} 
 
+    #[test]
+    #[serial_test::serial]
+    fn rotates_and_pops_the_stack() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::create_dir(root.join(name)).unwrap();
+        }
+        let mut ctx = ShellContext::new();
+        ctx.cwd = root.clone();
+        let mut run = |flavor: Flavor, list: &[&str]| {
+            let invocation = parse(flavor, list).unwrap();
+            let outcome = invocation.run(&mut ctx);
+            (outcome.status, String::from_utf8(outcome.stdout).unwrap())
+        };
+
+        run(Flavor::Pushd, &["a"]);
+        run(Flavor::Pushd, &["../b"]);
+        let (status, out) = run(Flavor::Pushd, &["../c"]);
+        assert_eq!(status, 0);
+        let names = |out: &str| -> Vec<String> {
+            out.split_whitespace()
+                .map(|p| {
+                    std::path::Path::new(p)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+        let (_, out) = run(Flavor::Dirs, &["-l"]);
+        let root_name = root.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(names(&out), ["c", "b", "a", root_name.as_str()]);
+
+        let (_, out) = run(Flavor::Pushd, &["+2"]);
+        assert_eq!(names(&out)[..2], ["a", root_name.as_str()]);
+        let (_, out) = run(Flavor::Popd, &["-0"]);
+        assert_eq!(names(&out), ["a", root_name.as_str(), "c"]);
+        let (_, out) = run(Flavor::Popd, &[]);
+        assert_eq!(names(&out), [root_name.as_str(), "c"]);
+        let (status, _) = run(Flavor::Popd, &["+5"]);
+        assert_eq!(status, 1);
+        run(Flavor::Dirs, &["-c"]);
+        let (status, _) = run(Flavor::Popd, &[]);
+        assert_eq!(status, 1);
+    }
+}
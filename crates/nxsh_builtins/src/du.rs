@@ -1,153 +1,660 @@
-//! `du` command - estimate file space usage.
-//! Usage: du [-h] [PATH]
-//!   -h : human readable units
-//! If PATH omitted, uses current directory.
-
-use anyhow::Result;
-use std::path::Path;
-use walkdir::WalkDir;
-
-// Beautiful CUI design
-use crate::ui_design::{ColorPalette, Icons};
-
-#[cfg(not(feature = "async-runtime"))]
-pub fn du_cli(args: &[String]) -> Result<()> {
-    let mut human = false;
-    let mut path = ".".to_string();
-    for arg in args {
-        if arg == "-h" {
-            human = true;
-            continue;
-        }
-        path = arg.clone();
-    }
-
-    let colors = ColorPalette::new();
-    let icons = Icons::new();
-
-    // Beautiful header
-    println!(
-        "\n{}{}┌─── {} Disk Usage Analysis for {} ───┐{}",
-        colors.primary,
-        "═".repeat(5),
-        Icons::FOLDER,
-        path,
-        colors.reset
-    );
-
-    let size = calc_size(Path::new(&path).to_path_buf())?;
-    let human_size = bytesize::ByteSize::b(size).to_string_as(true);
-
-    // Beautiful table output
-    let table = TableFormatter::new();
-    let rows = [
-        vec!["Path".to_string(), "Size".to_string(), "Type".to_string()],
-        vec![
-            path.to_string(),
-            if human {
-                human_size.to_string()
-            } else {
-                size.to_string()
-            },
-            "Directory".to_string(),
-        ],
-    ];
-
-    println!("{}", table.format());
-    Ok(())
-}
-
-#[cfg(feature = "async-runtime")]
-pub async fn du_cli(args: &[String]) -> Result<()> {
-    let mut human = false;
-    let mut path = ".".to_string();
-    for arg in args {
-        if arg == "-h" {
-            human = true;
-            continue;
-        }
-        path = arg.clone();
-    }
-
-    let colors = ColorPalette::new();
-    let icons = Icons::new();
-
-    // Beautiful header
-    println!(
-        "\n{}{}┌─── {} Disk Usage Analysis for {} ───┐{}",
-        colors.primary,
-        "═".repeat(5),
-        Icons::FOLDER,
-        path,
-        colors.reset
-    );
-
-    let size = calc_size(Path::new(&path).to_path_buf())?;
-    let human_size = bytesize::ByteSize::b(size).to_string_as(true);
-
-    // Beautiful table output
-    let mut table = TableFormatter::new();
-    table.add_row(vec![
-        "Path".to_string(),
-        "Size".to_string(),
-        "Type".to_string(),
-    ]);
-    table.add_row(vec![
-        path.to_string(),
-        if human { human_size } else { size.to_string() },
-        "Directory".to_string(),
-    ]);
-
-    println!("{}", table.format());
-    Ok(())
-}
-
-fn calc_size(path: std::path::PathBuf) -> Result<u64> {
-    let mut total = 0;
+//! `du` builtin - estimate the space files take
+//!
+//! Syntax:
+//!   du [-abchksx] [-d DEPTH] [-t SIZE] [--apparent-size] [--si] [FILE...]
+//!
+//! Prints the space taken by each directory under each FILE, or under the
+//! current directory, after the directories inside it. Sizes are the
+//! blocks allocated on disk counted in 1K, or with `--apparent-size` the
+//! lengths of the files; `-b` shows apparent sizes in bytes and `-h` and
+//! `--si` with a unit in powers of 1024 or 1000. A file with several hard
+//! links is counted once.
+//!
+//! `-a` lists the files too and `-s` only the FILEs themselves; `-d DEPTH`
+//! stops listing DEPTH levels below them, though everything is still
+//! counted. `-t SIZE` leaves out the entries smaller than SIZE, or larger
+//! for a negative SIZE, which may end in K, M, G or T. `-x` skips the
+//! directories on other file systems and `-c` adds a grand total.
+//!
+//! The directories are read by a thread per CPU, each taking the
+//! directories it finds and stealing from the others when it runs out, so
+//! a large tree is scanned in parallel; the output is sorted by name
+//! whatever order they finish in. The space of each file comes from
+//! [`nxsh_hal::fs::file_space`].
+//!
+//! In a structured pipeline `du` takes the same options and gives a table
+//! with the columns path, size, apparent, disk and files, the sizes in
+//! bytes and `size` the one du would show:
+//!
+//!   du -d 1 | sort-by size | last 5
+//!
+//! The exit status is 0 on success and 1 for an invalid option or when a
+//! FILE or directory could not be read.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{file_space, FileSpace};
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The `du` builtin command implementation
+pub struct DuCommand;
+
+impl Builtin for DuCommand {
+    fn name(&self) -> &'static str {
+        "du"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Estimate file space usage"
+    }
 
-    for entry in WalkDir::new(path) {
-        let entry = entry?;
-        if let Ok(metadata) = entry.metadata() {
-            if metadata.is_file() {
-                total += metadata.len();
+    fn description(&self) -> &'static str {
+        "Show the disk space, or the apparent size, of each directory under the given \
+         files, scanning the tree in parallel."
+    }
+
+    fn usage(&self) -> &'static str {
+        "du [-abchksx] [-d DEPTH] [-t SIZE] [--apparent-size] [--si] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Estimate file space usage. Use 'du -sh DIR' for a directory's total, \
+         'du -h -d 1' for the directories here, or 'du -d 1 | sort-by size' in a \
+         structured pipeline."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
+    }
+}
+
+/// Run du for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args, &std::env::current_dir()?);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
+}
+
+/// A line du prints, with its sizes in bytes
+pub(crate) struct Usage {
+    pub(crate) path: PathBuf,
+    /// The size du shows: apparent with `--apparent-size` or `-b`, on disk
+    /// otherwise
+    pub(crate) size: u64,
+    pub(crate) apparent: u64,
+    pub(crate) disk: u64,
+    /// The files under it, directories left out
+    pub(crate) files: u64,
+}
+
+/// The entries `args` select, for the structured `du`
+pub(crate) fn selected_usage(args: &[String], cwd: &Path) -> Result<Vec<Usage>, String> {
+    let options = Options::parse(args)?;
+    let scan = scan(&options, cwd);
+    match scan.errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(scan.entries),
+    }
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+fn run(args: &[String], cwd: &Path) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome {
+                stdout: String::new(),
+                stderr: format!("du: {message}\n"),
+                status: 1,
             }
         }
+    };
+    let scan = scan(&options, cwd);
+    let mut stdout = String::new();
+    for entry in &scan.entries {
+        stdout.push_str(&format!(
+            "{}\t{}\n",
+            options.show(entry.size),
+            entry.path.display()
+        ));
+    }
+    if options.total {
+        stdout.push_str(&format!("{}\ttotal\n", options.show(scan.total)));
+    }
+    Outcome {
+        stdout,
+        stderr: scan
+            .errors
+            .iter()
+            .map(|error| format!("du: {error}\n"))
+            .collect(),
+        status: if scan.errors.is_empty() { 0 } else { 1 },
     }
+}
 
-    Ok(total)
+/// Parsed command line
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// `-a`: files as well as directories
+    all: bool,
+    /// `--apparent-size`: lengths rather than blocks on disk
+    apparent: bool,
+    /// `-b`: apparent sizes in bytes
+    bytes: bool,
+    /// `-c`: a grand total
+    total: bool,
+    /// `-h` or `--si`: the power sizes are shown in
+    human: Option<u64>,
+    /// `-d`, or 0 for `-s`: how deep entries are listed
+    max_depth: Option<usize>,
+    /// `-t`: the smallest size listed, or the largest when negative
+    threshold: Option<i64>,
+    /// `-x`: stay on the file system of each FILE
+    one_file_system: bool,
+    files: Vec<String>,
 }
 
-// Import statements
-use crate::common::TableFormatter;
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut summarize = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (name, attached) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |option: &str| {
+                attached
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| format!("option requires an argument -- '{option}'"))
+            };
+            match name {
+                "--all" => options.all = true,
+                "--apparent-size" => options.apparent = true,
+                "--bytes" => options.bytes = true,
+                "--total" => options.total = true,
+                "--human-readable" => options.human = Some(1024),
+                "--si" => options.human = Some(1000),
+                "--summarize" => summarize = true,
+                "--one-file-system" => options.one_file_system = true,
+                "--max-depth" => options.max_depth = Some(parse_depth(&value("max-depth")?)?),
+                "--threshold" => options.threshold = Some(parse_threshold(&value("threshold")?)?),
+                "--" => {
+                    options.files.extend(args.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 => {
+                    for (at, flag) in flags.char_indices().skip(1) {
+                        match flag {
+                            'a' => options.all = true,
+                            'b' => options.bytes = true,
+                            'c' => options.total = true,
+                            'h' => options.human = Some(1024),
+                            'k' => options.human = None,
+                            's' => summarize = true,
+                            'x' => options.one_file_system = true,
+                            'd' | 't' => {
+                                // The value may follow in the same word
+                                let rest = &flags[at + 1..];
+                                let given = if rest.is_empty() {
+                                    value(&flag.to_string())?
+                                } else {
+                                    rest.to_string()
+                                };
+                                if flag == 'd' {
+                                    options.max_depth = Some(parse_depth(&given)?);
+                                } else {
+                                    options.threshold = Some(parse_threshold(&given)?);
+                                }
+                                break;
+                            }
+                            other => return Err(format!("invalid option -- '{other}'")),
+                        }
+                    }
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        if summarize {
+            match options.max_depth {
+                Some(depth) if depth > 0 => {
+                    return Err(format!("summarizing conflicts with --max-depth={depth}"));
+                }
+                _ => options.max_depth = Some(0),
+            }
+        }
+        if options.bytes {
+            options.apparent = true;
+        }
+        if options.files.is_empty() {
+            options.files.push(".".to_string());
+        }
+        Ok(options)
+    }
 
-pub fn execute(
-    args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    #[cfg(feature = "async-runtime")]
-    {
-        // Use blocking runtime for async code
-        use tokio::runtime::Runtime;
-        let rt =
-            Runtime::new().map_err(|e| crate::common::BuiltinError::Internal(e.to_string()))?;
-        rt.block_on(async {
-            match du_cli(args).await {
-                Ok(_) => Ok(0),
-                Err(e) => {
-                    eprintln!("du: {e}");
-                    Ok(1)
+    /// Whether an entry `depth` levels below its FILE taking `size` bytes is
+    /// listed
+    fn lists(&self, depth: usize, size: u64) -> bool {
+        self.max_depth.is_none_or(|max| depth <= max)
+            && match self.threshold {
+                Some(least) if least >= 0 => size >= least.unsigned_abs(),
+                Some(most) => size <= most.unsigned_abs(),
+                None => true,
+            }
+    }
+
+    /// A size in the unit asked for
+    fn show(&self, bytes: u64) -> String {
+        match self.human {
+            Some(base) => crate::df::human_size(bytes, base),
+            None if self.bytes => bytes.to_string(),
+            None => bytes.div_ceil(1024).to_string(),
+        }
+    }
+}
+
+fn parse_depth(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid maximum depth '{value}'"))
+}
+
+/// A `-t` SIZE: bytes, or with a K, M, G or T suffix in powers of 1024
+fn parse_threshold(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid --threshold argument '{value}'");
+    let (negative, size) = match value.strip_prefix('-') {
+        Some(size) => (true, size),
+        None => (false, value),
+    };
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let number: i64 = size[..digits].parse().map_err(|_| invalid())?;
+    let scale: i64 = match size[digits..].trim_end_matches(['B', 'i']) {
+        "" => 1,
+        "K" | "k" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let bytes = number.checked_mul(scale).ok_or_else(invalid)?;
+    match (negative, bytes) {
+        // There is nothing smaller than no bytes to ask for
+        (true, 0) => Err(invalid()),
+        (true, bytes) => Ok(-bytes),
+        (false, bytes) => Ok(bytes),
+    }
+}
+
+/// A file or directory found by the scan
+struct Node {
+    /// As shown: the FILE it was found under joined with the names below
+    path: PathBuf,
+    parent: Option<usize>,
+    depth: usize,
+    is_dir: bool,
+    /// Its own space, and once the scan is done everything under it too
+    apparent: u64,
+    disk: u64,
+    files: u64,
+}
+
+impl Node {
+    fn new(path: PathBuf, parent: Option<usize>, depth: usize, is_dir: bool) -> Self {
+        Node {
+            path,
+            parent,
+            depth,
+            is_dir,
+            apparent: 0,
+            disk: 0,
+            files: 0,
+        }
+    }
+
+    fn add(&mut self, space: &FileSpace, is_dir: bool) {
+        self.apparent += space.apparent;
+        self.disk += space.allocated;
+        if !is_dir {
+            self.files += 1;
+        }
+    }
+}
+
+/// A directory waiting to be read
+#[derive(Clone, Copy)]
+struct Task {
+    node: usize,
+    /// The file system of the FILE it is under, for `-x`
+    device: u64,
+}
+
+/// The entries to list in order, the sum of the FILEs and what could not
+/// be read
+struct Scan {
+    entries: Vec<Usage>,
+    total: u64,
+    errors: Vec<String>,
+}
+
+/// The shared state of the threads reading the directories
+struct Scanner<'a> {
+    options: &'a Options,
+    cwd: &'a Path,
+    nodes: Mutex<Vec<Node>>,
+    /// The directories each thread has found and not read yet; a thread
+    /// takes the newest of its own and steals the oldest of another's
+    queues: Vec<Mutex<VecDeque<Task>>>,
+    /// Directories queued or being read; the scan is over at zero
+    pending: AtomicUsize,
+    /// Hard linked files already counted
+    seen: Mutex<HashSet<(u64, u64)>>,
+    errors: Mutex<Vec<String>>,
+}
+
+fn scan(options: &Options, cwd: &Path) -> Scan {
+    let workers = std::thread::available_parallelism().map_or(1, usize::from);
+    let scanner = Scanner {
+        options,
+        cwd,
+        nodes: Mutex::new(Vec::new()),
+        queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+        pending: AtomicUsize::new(0),
+        seen: Mutex::new(HashSet::new()),
+        errors: Mutex::new(Vec::new()),
+    };
+
+    // The FILEs are counted here and their directories shared out
+    let mut roots = Vec::new();
+    for (i, file) in options.files.iter().enumerate() {
+        let path = cwd.join(file);
+        let space = std::fs::symlink_metadata(&path)
+            .map_err(|e| io_message(&e))
+            .and_then(|meta| {
+                let space = file_space(&path, &meta).map_err(|e| e.to_string())?;
+                Ok((meta.is_dir(), space))
+            });
+        let (is_dir, space) = match space {
+            Ok(found) => found,
+            Err(e) => {
+                scanner.error(format!("cannot access '{file}': {e}"));
+                continue;
+            }
+        };
+        let mut nodes = scanner.nodes.lock().unwrap();
+        let mut node = Node::new(PathBuf::from(file), None, 0, is_dir);
+        if is_dir || scanner.first_sight(&space) {
+            node.add(&space, is_dir);
+        }
+        roots.push(nodes.len());
+        if is_dir {
+            scanner.pending.fetch_add(1, Ordering::SeqCst);
+            scanner.queues[i % workers].lock().unwrap().push_back(Task {
+                node: nodes.len(),
+                device: space.device,
+            });
+        }
+        nodes.push(node);
+    }
+
+    std::thread::scope(|scope| {
+        for worker in 0..workers {
+            let scanner = &scanner;
+            scope.spawn(move || scanner.work(worker));
+        }
+    });
+
+    let mut nodes = scanner.nodes.into_inner().unwrap();
+    let errors = scanner.errors.into_inner().unwrap();
+
+    // Children come after their parent, so a pass from the end sums them up
+    let mut children = vec![Vec::new(); nodes.len()];
+    for i in (0..nodes.len()).rev() {
+        if let Some(parent) = nodes[i].parent {
+            let (apparent, disk, files) = (nodes[i].apparent, nodes[i].disk, nodes[i].files);
+            nodes[parent].apparent += apparent;
+            nodes[parent].disk += disk;
+            nodes[parent].files += files;
+            children[parent].push(i);
+        }
+    }
+    for list in &mut children {
+        list.sort_by(|&a, &b| nodes[a].path.cmp(&nodes[b].path));
+    }
+
+    let measure = |node: &Node| {
+        if options.apparent {
+            node.apparent
+        } else {
+            node.disk
+        }
+    };
+    let mut entries = Vec::new();
+    let mut total = 0;
+    for &root in &roots {
+        total += measure(&nodes[root]);
+        // Walked depth first, each entry listed after those under it
+        let mut stack = vec![(root, false)];
+        while let Some((at, expanded)) = stack.pop() {
+            if !expanded {
+                stack.push((at, true));
+                stack.extend(children[at].iter().rev().map(|&child| (child, false)));
+                continue;
+            }
+            let node = &nodes[at];
+            let size = measure(node);
+            if (node.is_dir || options.all || node.depth == 0) && options.lists(node.depth, size) {
+                entries.push(Usage {
+                    path: node.path.clone(),
+                    size,
+                    apparent: node.apparent,
+                    disk: node.disk,
+                    files: node.files,
+                });
+            }
+        }
+    }
+    Scan {
+        entries,
+        total,
+        errors,
+    }
+}
+
+impl Scanner<'_> {
+    fn error(&self, message: String) {
+        self.errors.lock().unwrap().push(message);
+    }
+
+    /// Whether a file is counted: always, unless it is a hard link to one
+    /// that already was
+    fn first_sight(&self, space: &FileSpace) -> bool {
+        match space.file_id {
+            Some(id) if space.links > 1 => self.seen.lock().unwrap().insert((space.device, id)),
+            _ => true,
+        }
+    }
+
+    /// Read directories until none are left anywhere
+    fn work(&self, worker: usize) {
+        let count = self.queues.len();
+        loop {
+            let own = self.queues[worker].lock().unwrap().pop_back();
+            let task = own.or_else(|| {
+                (1..count).find_map(|offset| {
+                    self.queues[(worker + offset) % count]
+                        .lock()
+                        .unwrap()
+                        .pop_front()
+                })
+            });
+            match task {
+                Some(task) => {
+                    self.read(worker, task);
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
                 }
+                None if self.pending.load(Ordering::SeqCst) == 0 => break,
+                // Another thread is still reading and may queue more
+                None => std::thread::sleep(Duration::from_millis(1)),
             }
-        })
+        }
     }
-    #[cfg(not(feature = "async-runtime"))]
-    {
-        match du_cli(args) {
-            Ok(_) => Ok(0),
+
+    /// Count the files of one directory and queue the directories in it
+    fn read(&self, worker: usize, task: Task) {
+        let (shown, depth) = {
+            let nodes = self.nodes.lock().unwrap();
+            (nodes[task.node].path.clone(), nodes[task.node].depth)
+        };
+        let entries = match std::fs::read_dir(self.cwd.join(&shown)) {
+            Ok(entries) => entries,
             Err(e) => {
-                eprintln!("du: {e}");
-                Ok(1)
+                self.error(format!(
+                    "cannot read directory '{}': {}",
+                    shown.display(),
+                    io_message(&e)
+                ));
+                return;
+            }
+        };
+
+        let mut own = Node::new(PathBuf::new(), None, depth, true);
+        let mut found = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.error(format!(
+                        "cannot read directory '{}': {}",
+                        shown.display(),
+                        io_message(&e)
+                    ));
+                    continue;
+                }
+            };
+            let path = shown.join(entry.file_name());
+            // Like symlink_metadata, a link is not followed
+            let space = entry
+                .metadata()
+                .map_err(|e| io_message(&e))
+                .and_then(|meta| {
+                    let space = file_space(entry.path(), &meta).map_err(|e| e.to_string())?;
+                    Ok((meta.is_dir(), space))
+                });
+            let (is_dir, space) = match space {
+                Ok(found) => found,
+                Err(e) => {
+                    self.error(format!("cannot access '{}': {e}", path.display()));
+                    continue;
+                }
+            };
+            if is_dir {
+                if self.options.one_file_system && space.device != task.device {
+                    continue;
+                }
+            } else if !self.first_sight(&space) {
+                continue;
+            }
+            if is_dir || self.options.all {
+                let mut node = Node::new(path, Some(task.node), depth + 1, is_dir);
+                node.add(&space, is_dir);
+                found.push(node);
+            } else {
+                own.add(&space, false);
             }
         }
+
+        let mut queued = Vec::new();
+        {
+            let mut nodes = self.nodes.lock().unwrap();
+            let this = &mut nodes[task.node];
+            this.apparent += own.apparent;
+            this.disk += own.disk;
+            this.files += own.files;
+            for node in found {
+                if node.is_dir {
+                    queued.push(Task {
+                        node: nodes.len(),
+                        device: task.device,
+                    });
+                }
+                nodes.push(node);
+            }
+        }
+        // Counted before this directory is done, so the total never
+        // touches zero while work is left
+        self.pending.fetch_add(queued.len(), Ordering::SeqCst);
+        self.queues[worker].lock().unwrap().extend(queued);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_combine_and_take_values() {
+        let options = Options::parse(&args(&["-ahc", "-d1", "-t", "-2K", "src"])).unwrap();
+        assert!(options.all && options.total);
+        assert_eq!(options.human, Some(1024));
+        assert_eq!(options.max_depth, Some(1));
+        assert_eq!(options.threshold, Some(-2048));
+        assert_eq!(options.files, ["src"]);
+
+        let options = Options::parse(&args(&["-sb"])).unwrap();
+        assert_eq!(options.max_depth, Some(0));
+        assert!(options.apparent);
+        assert_eq!(options.files, ["."]);
+
+        assert_eq!(
+            Options::parse(&args(&["-s", "-d", "2"])).unwrap_err(),
+            "summarizing conflicts with --max-depth=2"
+        );
+        assert!(Options::parse(&args(&["-q"])).is_err());
+        assert!(Options::parse(&args(&["-d"])).is_err());
+    }
+
+    #[test]
+    fn thresholds_take_units_and_a_sign() {
+        assert_eq!(parse_threshold("100"), Ok(100));
+        assert_eq!(parse_threshold("4K"), Ok(4096));
+        assert_eq!(parse_threshold("1MiB"), Ok(1 << 20));
+        assert_eq!(parse_threshold("-1G"), Ok(-(1 << 30)));
+        assert!(parse_threshold("-0").is_err());
+        assert!(parse_threshold("12Q").is_err());
+
+        let options = Options::parse(&args(&["-t", "-100"])).unwrap();
+        assert!(options.lists(3, 100));
+        assert!(!options.lists(3, 101));
+        let options = Options::parse(&args(&["-d", "1", "-t", "100"])).unwrap();
+        assert!(!options.lists(1, 99));
+        assert!(!options.lists(2, 1000));
     }
 }
@@ -0,0 +1,386 @@
+//! `elevate` and `runas` builtins - run a command with administrative rights
+//!
+//! Syntax:
+//!   elevate [-b BACKEND] [-u USER] COMMAND [ARG]...
+//!   elevate [-b BACKEND] -s
+//!
+//! COMMAND is looked up in PATH and run as the administrator, or as USER
+//! with `-u`, from the shell's working directory. `runas` is the same
+//! command under its Windows name. When the shell already has
+//! administrative rights COMMAND simply runs.
+//!
+//! On Unix the rights come from the first of `sudo`, `doas` and `pkexec`
+//! found in PATH, or the one named by `-b`, which asks for credentials on
+//! the terminal; `sudo` names the command in its password prompt. On
+//! Windows the UAC consent prompt is shown and COMMAND runs in a console of
+//! its own, so its output is not seen by the shell, and `-u` is not
+//! available.
+//!
+//! Options:
+//!   -b BACKEND  elevate through BACKEND: sudo, doas, pkexec or uac
+//!   -u USER     run as USER rather than the administrator
+//!   -s          replace the shell with an elevated one; on Windows the
+//!               elevated shell opens in a new window instead
+//!
+//! The exit status is COMMAND's. It is 1 when elevation fails or is
+//! declined, 2 on usage errors and 127 when COMMAND is not found.
+
+use crate::common::{hal_message, io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::command::find_in_path;
+use nxsh_hal::privilege::{self, Backend, Elevation};
+use nxsh_hal::HalError;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The `elevate` builtin command implementation
+pub struct ElevateCommand;
+
+/// The `runas` builtin command implementation
+pub struct RunasCommand;
+
+impl Builtin for ElevateCommand {
+    fn name(&self) -> &'static str {
+        "elevate"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run a command with administrative rights"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run COMMAND as the administrator through sudo, doas or pkexec on Unix and the UAC \
+         prompt on Windows, or replace the shell with an elevated one with -s."
+    }
+
+    fn usage(&self) -> &'static str {
+        Flavor::Elevate.usage()
+    }
+
+    fn help(&self) -> &'static str {
+        "Gain administrative rights. Use 'elevate systemctl restart nginx' to run one \
+         command as the administrator or 'elevate -s' for an elevated shell."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Elevate, args)
+    }
+}
+
+impl Builtin for RunasCommand {
+    fn name(&self) -> &'static str {
+        "runas"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run a command with administrative rights"
+    }
+
+    fn description(&self) -> &'static str {
+        "The same as elevate."
+    }
+
+    fn usage(&self) -> &'static str {
+        Flavor::Runas.usage()
+    }
+
+    fn help(&self) -> &'static str {
+        "Gain administrative rights. Use 'runas notepad C:\\Windows\\System32\\drivers\\etc\\hosts' \
+         to edit a protected file."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Runas, args)
+    }
+}
+
+fn execute_in(
+    ctx: &mut ShellContext,
+    flavor: Flavor,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let path = ctx
+        .get_var("PATH")
+        .map(OsString::from)
+        .or_else(|| std::env::var_os("PATH"))
+        .unwrap_or_default();
+    let outcome = run(flavor, args, &path, &ctx.cwd);
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+/// Run elevate for the legacy dispatcher
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Elevate, args)
+}
+
+/// Run runas for the legacy dispatcher
+pub fn execute_runas(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Runas, args)
+}
+
+fn run_legacy(flavor: Flavor, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let outcome = run(flavor, args, &path, &cwd);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Which name the command was run by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Elevate,
+    Runas,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Elevate => "elevate",
+            Flavor::Runas => "runas",
+        }
+    }
+
+    fn usage(self) -> &'static str {
+        match self {
+            Flavor::Elevate => "elevate [-b BACKEND] [-u USER] COMMAND [ARG]... | elevate -s",
+            Flavor::Runas => "runas [-b BACKEND] [-u USER] COMMAND [ARG]... | runas -s",
+        }
+    }
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn failure(flavor: Flavor, status: i32, message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("{}: {message}\n", flavor.name()).into_bytes(),
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    backend: Option<Backend>,
+    user: Option<String>,
+    shell: bool,
+    command: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.command.extend(rest.cloned());
+                    break;
+                }
+                "-s" => options.shell = true,
+                "-b" | "-u" => {
+                    let value = rest
+                        .next()
+                        .ok_or_else(|| format!("{arg}: option requires an argument"))?;
+                    if arg == "-u" {
+                        options.user = Some(value.clone());
+                    } else {
+                        let backend = Backend::from_name(value)
+                            .ok_or_else(|| format!("{value}: unknown backend"))?;
+                        options.backend = Some(backend);
+                    }
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    return Err(format!("{option}: invalid option"));
+                }
+                _ => {
+                    options.command.push(arg.clone());
+                    options.command.extend(rest.cloned());
+                    break;
+                }
+            }
+        }
+        match (options.shell, options.command.is_empty()) {
+            (true, false) => Err("-s takes no command".to_string()),
+            (false, true) => Err("a command is required".to_string()),
+            _ => Ok(options),
+        }
+    }
+}
+
+fn run(flavor: Flavor, args: &[String], path: &OsStr, cwd: &Path) -> Outcome {
+    let name = flavor.name();
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome::failure(
+                flavor,
+                2,
+                format!("{message}\n{name}: usage: {}", flavor.usage()),
+            )
+        }
+    };
+
+    let program = if options.shell {
+        match std::env::current_exe() {
+            Ok(exe) => exe,
+            Err(e) => return Outcome::failure(flavor, 1, format!("cannot find the shell: {e}")),
+        }
+    } else {
+        let command = &options.command[0];
+        match find(command, path, cwd) {
+            Some(program) => program,
+            None => return Outcome::failure(flavor, 127, format!("{command}: command not found")),
+        }
+    };
+
+    // Already privileged: nothing to ask for
+    if privilege::is_elevated() && options.user.is_none() && options.backend.is_none() {
+        if options.shell {
+            return Outcome::failure(flavor, 1, "the shell is already elevated".to_string());
+        }
+        return run_directly(flavor, &program, &options.command[1..], cwd);
+    }
+
+    let launcher = match options.backend {
+        Some(backend) => backend
+            .locate(path)
+            .ok_or_else(|| format!("{}: not available on this system", backend.name())),
+        None => privilege::find_launcher(path)
+            .ok_or_else(|| "no way to elevate was found; install sudo, doas or polkit".to_string()),
+    };
+    let launcher = match launcher {
+        Ok(launcher) => launcher,
+        Err(message) => return Outcome::failure(flavor, 1, message),
+    };
+    let backend = launcher.backend();
+
+    let mut elevation = Elevation::new(launcher, &program);
+    elevation.current_dir(cwd);
+    if let Some(user) = &options.user {
+        elevation.user(user);
+    }
+    if options.shell {
+        elevation.prompt(&format!(
+            "[{name}] password for %p to start an elevated shell: "
+        ));
+        return match elevation.exec() {
+            Ok(()) => Outcome {
+                stdout: Vec::new(),
+                stderr: format!("{name}: started an elevated shell in a new window\n").into_bytes(),
+                status: 0,
+            },
+            Err(e) => Outcome::failure(flavor, 1, reason(backend, &e)),
+        };
+    }
+
+    // sudo expands %-sequences in the prompt, so a literal % is doubled
+    let shown = options.command[0].replace('%', "%%");
+    elevation
+        .prompt(&format!("[{name}] password for %p to run {shown}: "))
+        .args(&options.command[1..]);
+    match elevation.output() {
+        Ok(result) => Outcome {
+            stdout: result.stdout,
+            stderr: result.stderr,
+            status: result.exit_code,
+        },
+        Err(e) => Outcome::failure(flavor, 1, reason(backend, &e)),
+    }
+}
+
+/// Run PROGRAM with the rights the shell already has
+fn run_directly(flavor: Flavor, program: &Path, args: &[String], cwd: &Path) -> Outcome {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .current_dir(cwd)
+        .stdin(std::process::Stdio::inherit())
+        .output();
+    match output {
+        Ok(output) => Outcome {
+            stdout: output.stdout,
+            stderr: output.stderr,
+            status: exit_status(output.status),
+        },
+        Err(e) => Outcome::failure(
+            flavor,
+            126,
+            format!("{}: {}", program.display(), io_message(&e)),
+        ),
+    }
+}
+
+/// Full path of COMMAND in PATH; names with a slash are taken relative to
+/// CWD
+fn find(command: &str, path: &OsStr, cwd: &Path) -> Option<PathBuf> {
+    if command.contains('/') || (cfg!(windows) && command.contains('\\')) {
+        let full = cwd.join(command);
+        return full.exists().then_some(full);
+    }
+    find_in_path(command, path)
+}
+
+fn exit_status(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Why elevation failed, in a form fit for the user
+fn reason(backend: Backend, error: &HalError) -> String {
+    match error {
+        HalError::Security(e) => e.message.clone(),
+        HalError::Unsupported(message) => format!("{}: {message} is not supported", backend.name()),
+        other => format!("{}: {}", backend.name(), hal_message(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn options_stop_at_the_command() {
+        let options = parse(&["-b", "doas", "-u", "backup", "tar", "-c", "/srv"]).unwrap();
+        assert_eq!(options.backend, Some(Backend::Doas));
+        assert_eq!(options.user.as_deref(), Some("backup"));
+        assert_eq!(options.command, ["tar", "-c", "/srv"]);
+
+        assert!(parse(&["-s"]).unwrap().shell);
+        assert_eq!(parse(&["--", "-x"]).unwrap().command, ["-x"]);
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        assert_eq!(parse(&[]), Err("a command is required".to_string()));
+        assert_eq!(parse(&["-s", "ls"]), Err("-s takes no command".to_string()));
+        assert_eq!(
+            parse(&["-b", "su", "ls"]),
+            Err("su: unknown backend".to_string())
+        );
+        assert_eq!(
+            parse(&["-u"]),
+            Err("-u: option requires an argument".to_string())
+        );
+    }
+}
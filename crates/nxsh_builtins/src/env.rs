@@ -1,175 +1,422 @@
-use crate::command::{CommandInfo, CommandResult, CommandType, ShellState};
-use std::env;
+//! `env` builtin - print the environment or run a command in a modified one
+//!
+//! Syntax:
+//!   env [-0i] [-u NAME]... [NAME=VALUE]...
+//!   env [-i] [-u NAME]... [-C DIR] [NAME=VALUE]... COMMAND [ARG]...
+//!
+//! The environment starts as the shell's exported variables, or empty with
+//! `-i` (or a lone `-`). Each `-u` removes a variable from it and each
+//! NAME=VALUE sets one. Without a COMMAND the result is printed as sorted
+//! NAME=VALUE lines, ended by NUL instead of a newline with `-0`. With one,
+//! COMMAND is looked up in the new environment's PATH and run with exactly
+//! that environment, from DIR with `-C`. The shell's own variables are
+//! never changed.
+//!
+//! Options:
+//!   -i, --ignore-environment  start with an empty environment
+//!   -0, --null                end each printed variable with NUL
+//!   -u, --unset=NAME          remove NAME from the environment
+//!   -C, --chdir=DIR           run COMMAND in DIR
+//!
+//! The exit status is COMMAND's, or 0 when the environment is printed. It
+//! is 125 when env itself fails, 126 when COMMAND cannot be run and 127
+//! when it is not found.
 
-/// Environment variable management command
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// The `env` builtin command implementation
 pub struct EnvCommand;
 
-impl EnvCommand {
-    fn info(&self) -> CommandInfo {
-        CommandInfo {
-            command_type: CommandType::Builtin,
-            path: None,
-            name: "env".to_string(),
-            description: "Display or set environment variables".to_string(),
-            usage: "env [OPTION]... [NAME[=VALUE]]...".to_string(),
-            examples: vec![
-                "env".to_string(),
-                "env PATH".to_string(),
-                "env FOO=bar".to_string(),
-                "env -u PATH".to_string(),
-                "env --help".to_string(),
-            ],
+impl Builtin for EnvCommand {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print the environment or run a command in a modified one"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the exported variables, or run COMMAND with variables added, removed or \
+         cleared and optionally from another directory, leaving the shell's own variables \
+         alone."
+    }
+
+    fn usage(&self) -> &'static str {
+        "env [-0i] [-u NAME]... [-C DIR] [NAME=VALUE]... [COMMAND [ARG]...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the environment or run a command in a modified one. Use \
+         'env -i PATH=/bin HOME=/tmp sh' for a clean environment."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &exported(ctx), &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run env for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &std::env::vars().collect(), &cwd);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// The variables `env ARGS` prints, in order, for the structured `env`
+/// table; ARGS may not name a command
+pub fn listed_variables(
+    ctx: &ShellContext,
+    args: &[String],
+) -> Result<Vec<(String, String)>, String> {
+    let options = Options::parse(args)?;
+    if let Some(command) = options.command.first() {
+        return Err(format!("{command}: commands cannot start a table pipeline"));
+    }
+    Ok(options.environment(&exported(ctx)).into_iter().collect())
+}
+
+/// The shell's exported variables
+pub(crate) fn exported(ctx: &ShellContext) -> BTreeMap<String, String> {
+    ctx.env
+        .read()
+        .map(|env| env.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .unwrap_or_default()
+}
+
+/// Where commands are looked for when the environment has no PATH
+#[cfg(unix)]
+const DEFAULT_PATH: Option<&str> = Some("/bin:/usr/bin");
+#[cfg(not(unix))]
+const DEFAULT_PATH: Option<&str> = None;
+
+/// Status for failures of env itself
+const CANCELED: i32 = 125;
+
+const HELP: &str = "\
+Usage: env [OPTION]... [-] [NAME=VALUE]... [COMMAND [ARG]...]
+Set each NAME to VALUE in the environment and run COMMAND.
+
+  -i, --ignore-environment  start with an empty environment
+  -0, --null                end each output line with NUL, not newline
+  -u, --unset=NAME          remove variable from the environment
+  -C, --chdir=DIR           change working directory to DIR
+      --help                display this help and exit
+
+A mere - implies -i. If no COMMAND, print the resulting environment.
+";
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn failure(status: i32, message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("env: {message}\n").into_bytes(),
+            status,
         }
     }
+}
 
-    fn execute(&self, args: &[String], _state: &mut ShellState) -> CommandResult {
-        let mut show_help = false;
-        let mut null_separator = false;
-        let mut ignore_env = false;
-        let mut unset_vars: Vec<String> = Vec::new();
-        let mut set_vars: Vec<(String, String)> = Vec::new();
-        let mut query_vars: Vec<String> = Vec::new();
-
-        let mut i = 0;
-        while i < args.len() {
-            match args[i].as_str() {
-                "--help" => show_help = true,
-                "-0" | "--null" => null_separator = true,
-                "-i" | "--ignore-environment" => ignore_env = true,
-                "-u" | "--unset" => {
-                    if i + 1 < args.len() {
-                        unset_vars.push(args[i + 1].clone());
-                        i += 1;
-                    } else {
-                        return CommandResult::error("--unset requires a variable name");
-                    }
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    ignore: bool,
+    null: bool,
+    help: bool,
+    unset: Vec<String>,
+    chdir: Option<String>,
+    assignments: Vec<(String, String)>,
+    command: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut index = 0;
+        // The value of an option taking one, attached or in the next word
+        let value = |attached: &str, index: &mut usize| {
+            if !attached.is_empty() {
+                return Some(attached.to_string());
+            }
+            *index += 1;
+            args.get(*index).cloned()
+        };
+        while let Some(arg) = args.get(index) {
+            match arg.as_str() {
+                "--" => {
+                    index += 1;
+                    break;
+                }
+                "-" | "--ignore-environment" => options.ignore = true,
+                "--null" => options.null = true,
+                "--help" => {
+                    options.help = true;
+                    return Ok(options);
                 }
-                arg if arg.contains('=') => {
-                    if let Some((key, value)) = arg.split_once('=') {
-                        set_vars.push((key.to_string(), value.to_string()));
+                long if long.starts_with("--") => {
+                    let (name, attached) = long.split_once('=').unwrap_or((long, ""));
+                    let taken = match name {
+                        "--unset" | "--chdir" => value(attached, &mut index)
+                            .ok_or_else(|| format!("option '{name}' requires an argument"))?,
+                        _ => return Err(format!("unrecognized option '{long}'")),
+                    };
+                    if name == "--unset" {
+                        options.unset.push(taken);
                     } else {
-                        return CommandResult::error(&format!("Invalid assignment: {arg}"));
+                        options.chdir = Some(taken);
                     }
                 }
-                arg if !arg.starts_with('-') => {
-                    query_vars.push(arg.to_string());
-                }
-                _ => {
-                    return CommandResult::error(&format!("Unknown option: {}", args[i]));
+                cluster if cluster.starts_with('-') => {
+                    for (at, flag) in cluster.char_indices().skip(1) {
+                        match flag {
+                            'i' => options.ignore = true,
+                            '0' => options.null = true,
+                            'u' | 'C' => {
+                                let attached = &cluster[at + 1..];
+                                let taken = value(attached, &mut index).ok_or_else(|| {
+                                    format!("option requires an argument -- '{flag}'")
+                                })?;
+                                if flag == 'u' {
+                                    options.unset.push(taken);
+                                } else {
+                                    options.chdir = Some(taken);
+                                }
+                                break;
+                            }
+                            other => return Err(format!("invalid option -- '{other}'")),
+                        }
+                    }
                 }
+                _ => break,
             }
-            i += 1;
+            index += 1;
         }
 
-        if show_help {
-            return self.show_help();
+        let mut rest = args[index..].iter();
+        for arg in rest.by_ref() {
+            match arg.split_once('=') {
+                Some(("", _)) => return Err(format!("cannot set '{arg}': Invalid argument")),
+                Some((name, value)) => options
+                    .assignments
+                    .push((name.to_string(), value.to_string())),
+                None => {
+                    options.command.push(arg.clone());
+                    break;
+                }
+            }
         }
+        options.command.extend(rest.cloned());
 
-        // Handle unset operations
-        for var in &unset_vars {
-            env::remove_var(var);
+        if let Some(name) = options
+            .unset
+            .iter()
+            .find(|name| name.is_empty() || name.contains('='))
+        {
+            return Err(format!("cannot unset '{name}': Invalid argument"));
         }
-
-        // Handle set operations
-        for (key, value) in &set_vars {
-            env::set_var(key, value);
+        if options.null && !options.command.is_empty() {
+            return Err("cannot specify --null (-0) with command".to_string());
         }
-
-        // Handle queries or display all variables
-        if query_vars.is_empty() {
-            self.display_all_env_vars(null_separator, ignore_env)
-        } else {
-            self.display_specific_env_vars(&query_vars, null_separator)
+        if options.chdir.is_some() && options.command.is_empty() {
+            return Err("must specify command with --chdir (-C)".to_string());
         }
+        Ok(options)
     }
-}
-
-impl EnvCommand {
-    fn show_help(&self) -> CommandResult {
-        let help_text = r#"Usage: env [OPTION]... [NAME[=VALUE]]... [COMMAND [ARG]...]
-Set each NAME to VALUE in the environment and run COMMAND.
 
-  -i, --ignore-environment  start with an empty environment
-  -0, --null               end each output line with NUL, not newline
-  -u, --unset=NAME         remove variable from the environment
-      --help     display this help and exit
-      --version  output version information and exit
-
-If no COMMAND, print the resulting environment.
-
-Examples:
-  env                      Display all environment variables
-  env PATH                 Display the PATH variable
-  env FOO=bar              Set FOO to 'bar' and display all variables
-  env -u PATH              Remove PATH from environment
-  env FOO=bar COMMAND      Set FOO and run COMMAND with new environment
-"#;
-        CommandResult::success(help_text)
-    }
-
-    fn display_all_env_vars(&self, null_separator: bool, ignore_env: bool) -> CommandResult {
-        let mut output = String::new();
-        let separator = if null_separator { '\0' } else { '\n' };
-
-        if ignore_env {
-            // When ignoring environment, only show variables we've explicitly set
-            // For now, we'll show nothing since we don't track explicitly set vars
-            return CommandResult::success("");
+    /// The environment COMMAND sees, starting from the shell's EXPORTED
+    /// variables
+    fn environment(&self, exported: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mut env = if self.ignore {
+            BTreeMap::new()
+        } else {
+            exported.clone()
+        };
+        for name in &self.unset {
+            env.remove(name);
         }
-
-        let mut env_vars: Vec<(String, String)> = env::vars().collect();
-        env_vars.sort_by(|a, b| a.0.cmp(&b.0));
-
-        for (key, value) in env_vars {
-            output.push_str(&format!("{key}={value}{separator}"));
+        for (name, value) in &self.assignments {
+            env.insert(name.clone(), value.clone());
         }
+        env
+    }
+}
 
-        // Remove the trailing separator if present
-        if output.ends_with(separator) {
-            output.pop();
+fn run(args: &[String], exported: &BTreeMap<String, String>, cwd: &Path) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome::failure(
+                CANCELED,
+                format!("{message}\nTry 'env --help' for more information."),
+            )
         }
-
-        CommandResult::success(&output)
+    };
+    if options.help {
+        return Outcome {
+            stdout: HELP.as_bytes().to_vec(),
+            stderr: Vec::new(),
+            status: 0,
+        };
+    }
+    let env = options.environment(exported);
+    if options.command.is_empty() {
+        let end = if options.null { '\0' } else { '\n' };
+        let listing: String = env
+            .iter()
+            .map(|(name, value)| format!("{name}={value}{end}"))
+            .collect();
+        return Outcome {
+            stdout: listing.into_bytes(),
+            stderr: Vec::new(),
+            status: 0,
+        };
     }
 
-    fn display_specific_env_vars(&self, vars: &[String], null_separator: bool) -> CommandResult {
-        let mut output = String::new();
-        let separator = if null_separator { '\0' } else { '\n' };
-
-        for var in vars {
-            match env::var(var) {
-                Ok(value) => {
-                    output.push_str(&format!("{var}={value}{separator}"));
+    let dir = match &options.chdir {
+        Some(dir) => {
+            let full = cwd.join(dir);
+            match std::fs::metadata(&full) {
+                Ok(meta) if meta.is_dir() => full,
+                Ok(_) => {
+                    return Outcome::failure(
+                        CANCELED,
+                        format!("cannot change directory to '{dir}': Not a directory"),
+                    )
                 }
-                Err(env::VarError::NotPresent) => {
-                    return CommandResult::error(&format!("env: {var}: not set"));
-                }
-                Err(env::VarError::NotUnicode(_)) => {
-                    return CommandResult::error(&format!("env: {var}: contains invalid Unicode"));
+                Err(e) => {
+                    return Outcome::failure(
+                        CANCELED,
+                        format!("cannot change directory to '{dir}': {}", io_message(&e)),
+                    )
                 }
             }
         }
-
-        // Remove the trailing separator if present
-        if output.ends_with(separator) {
-            output.pop();
+        None => cwd.to_path_buf(),
+    };
+    let name = &options.command[0];
+    let Some(program) = find(name, &env, &dir) else {
+        return Outcome::failure(127, format!("'{name}': No such file or directory"));
+    };
+    let mut command = Command::new(&program);
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::arg0(&mut command, name);
+    command
+        .args(&options.command[1..])
+        .current_dir(&dir)
+        .env_clear()
+        .envs(&env)
+        .stdin(Stdio::inherit());
+    match command.output() {
+        Ok(output) => Outcome {
+            stdout: output.stdout,
+            stderr: output.stderr,
+            status: exit_status(output.status),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Outcome::failure(127, format!("'{name}': {}", io_message(&e)))
         }
+        Err(e) => Outcome::failure(126, format!("'{name}': {}", io_message(&e))),
+    }
+}
 
-        CommandResult::success(&output)
+/// Full path of COMMAND, searched for in the PATH of ENV; names with a
+/// slash are taken relative to DIR
+pub(crate) fn find(command: &str, env: &BTreeMap<String, String>, dir: &Path) -> Option<PathBuf> {
+    if command.contains('/') || (cfg!(windows) && command.contains('\\')) {
+        return Some(dir.join(command));
     }
+    let path = env.get("PATH").map(String::as_str).or(DEFAULT_PATH)?;
+    which::which_in(command, Some(path), dir).ok()
 }
 
-impl Default for EnvCommand {
-    fn default() -> Self {
-        Self
+/// The shell status for how a command ended: its exit code, or 128 plus
+/// the signal that killed it
+fn exit_status(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+            return 128 + signal;
+        }
     }
+    status.code().unwrap_or(1)
 }
 
-/// Execute function stub
-pub fn execute(
-    _args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parses_options_assignments_and_command() {
+        let options = parse(&["-iuHOME", "-C", "/tmp", "A=1", "B=x=y", "ls", "C=2"]).unwrap();
+        assert!(options.ignore && !options.null);
+        assert_eq!(options.unset, ["HOME"]);
+        assert_eq!(options.chdir.as_deref(), Some("/tmp"));
+        assert_eq!(
+            options.assignments,
+            [
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "x=y".to_string())
+            ]
+        );
+        assert_eq!(options.command, ["ls", "C=2"]);
+
+        let options = parse(&["-", "--unset=X", "--chdir", "d", "--", "-v"]).unwrap();
+        assert!(options.ignore);
+        assert_eq!(options.unset, ["X"]);
+        assert_eq!(options.command, ["-v"]);
+
+        assert_eq!(parse(&["-x"]).unwrap_err(), "invalid option -- 'x'");
+        assert_eq!(
+            parse(&["-u"]).unwrap_err(),
+            "option requires an argument -- 'u'"
+        );
+        assert!(parse(&["-0", "true"]).is_err());
+        assert!(parse(&["-C", "/"]).is_err());
+        assert!(parse(&["-u", "A=B"]).is_err());
+        assert!(parse(&["=x"]).is_err());
+    }
+
+    #[test]
+    fn builds_the_environment() {
+        let exported = BTreeMap::from([
+            ("HOME".to_string(), "/home/me".to_string()),
+            ("PATH".to_string(), "/bin".to_string()),
+        ]);
+        let env = parse(&["-u", "HOME", "X=1"])
+            .unwrap()
+            .environment(&exported);
+        assert_eq!(env.keys().collect::<Vec<_>>(), ["PATH", "X"]);
+        let env = parse(&["-i", "PATH=/usr/bin"])
+            .unwrap()
+            .environment(&exported);
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            [("PATH".to_string(), "/usr/bin".to_string())]
+        );
+
+        let outcome = run(&["-0".to_string()], &exported, Path::new("."));
+        assert_eq!(outcome.stdout, b"HOME=/home/me\0PATH=/bin\0");
+    }
 }
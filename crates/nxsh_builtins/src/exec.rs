@@ -1,35 +1,20 @@
 //! `exec` builtin  Ereplace the current shell process with the specified command.
 //! Usage: `exec CMD [ARGS...]`
-//! On Unix this calls `nix::unistd::execvp`. On Windows it spawns the process
-//! and exits with its status (best-effort emulation).
+//! On Unix this calls `execvp` through `nxsh_hal::process::replace_process`.
+//! On Windows it spawns the process and exits with its status (best-effort
+//! emulation).
 
 use anyhow::{anyhow, Result};
 
 pub fn exec_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
+    let Some((command, rest)) = args.split_first() else {
         return Err(anyhow!("exec: missing command"));
-    }
-    #[cfg(unix)]
-    {
-        use nix::unistd::fork;
-        use nix::unistd::{execvp, ForkResult};
-        use std::ffi::CString;
-
-        let c_cmd = CString::new(args[0].as_str())?;
-        let c_args: Vec<CString> = args
-            .iter()
-            .map(|s| CString::new(s.as_str()).unwrap())
-            .collect();
-        // Replace process image
-        execvp(&c_cmd, &c_args)?;
-        unreachable!();
-    }
-    #[cfg(windows)]
-    {
-        use std::process::Command;
-        let status = Command::new(&args[0]).args(&args[1..]).status()?;
-        std::process::exit(status.code().unwrap_or(1));
-    }
+    };
+    let mut cmd = std::process::Command::new(command);
+    cmd.args(rest);
+    // Only returns when the program could not be started
+    let err = nxsh_hal::process::replace_process(&mut cmd);
+    Err(anyhow!("{command}: {err}"))
 }
 
 /// Execute exec command
@@ -0,0 +1,313 @@
+//! `factor` builtin - print the prime factors of numbers
+//!
+//! Syntax:
+//!   factor [-h] [NUMBER...]
+//!
+//! Each NUMBER, or each whitespace separated word of standard input when
+//! there are none, is printed as `NUMBER: P1 P2 ...` with its prime factors
+//! in ascending order and repeated by multiplicity. `-h` prints a repeated
+//! factor once as `P^E`. Numbers up to 2^64 - 1 are factored, by trial
+//! division of the small primes and Pollard's rho for the rest.
+//!
+//! The exit status is 0 on success and 1 when a NUMBER is not a valid
+//! positive integer or is too large.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+/// The `factor` builtin command implementation
+pub struct FactorCommand;
+
+impl Builtin for FactorCommand {
+    fn name(&self) -> &'static str {
+        "factor"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print the prime factors of numbers"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print each NUMBER, read from standard input when there are none, followed by \
+         its prime factors."
+    }
+
+    fn usage(&self) -> &'static str {
+        "factor [-h] [NUMBER...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the prime factors of numbers. Use 'factor 360' to get '360: 2 2 2 3 3 5' \
+         or 'factor -h 360' for '360: 2^3 3^2 5'."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run factor for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Bases that make Miller-Rabin exact for every 64-bit number
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in WITNESSES {
+        if n % p == 0 {
+            return n == p;
+        }
+    }
+    let shift = (n - 1).trailing_zeros();
+    let odd = (n - 1) >> shift;
+    'witness: for a in WITNESSES {
+        let mut x = pow_mod(a, odd, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..shift {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// A nontrivial divisor of N, an odd composite, by Pollard's rho
+fn rho(n: u64) -> u64 {
+    let mut c = 1;
+    loop {
+        let step = |x: u64| ((x as u128 * x as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            x = step(x);
+            y = step(step(y));
+            divisor = gcd(x.abs_diff(y), n);
+        }
+        // The cycle closed without splitting N; try another polynomial
+        if divisor != n {
+            return divisor;
+        }
+        c += 1;
+    }
+}
+
+/// The prime factors of N in ascending order
+fn factors(mut n: u64) -> Vec<u64> {
+    let mut found = Vec::new();
+    if n < 2 {
+        return found;
+    }
+    while n % 2 == 0 {
+        found.push(2);
+        n /= 2;
+    }
+    let mut p = 3;
+    while p < 1000 && p * p <= n {
+        while n % p == 0 {
+            found.push(p);
+            n /= p;
+        }
+        p += 2;
+    }
+    let mut pending = vec![n];
+    while let Some(m) = pending.pop() {
+        if m == 1 {
+            continue;
+        }
+        if is_prime(m) {
+            found.push(m);
+        } else {
+            let divisor = rho(m);
+            pending.extend([divisor, m / divisor]);
+        }
+    }
+    found.sort_unstable();
+    found
+}
+
+/// The output line for TOKEN, or the error message for it
+fn line(token: &str, exponents: bool) -> Result<String, String> {
+    let n: u64 = token.parse().map_err(|_| {
+        let digits = token.strip_prefix('+').unwrap_or(token);
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            format!("'{token}' is too large")
+        } else {
+            format!("'{token}' is not a valid positive integer")
+        }
+    })?;
+    let mut out = format!("{n}:");
+    let found = factors(n);
+    let mut rest = &found[..];
+    while let Some(&p) = rest.first() {
+        let repeats = if exponents {
+            rest.iter().take_while(|&&q| q == p).count()
+        } else {
+            1
+        };
+        let _ = match repeats {
+            1 => write!(out, " {p}"),
+            _ => write!(out, " {p}^{repeats}"),
+        };
+        rest = &rest[repeats..];
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+fn run(args: &[String], stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let mut exponents = false;
+    let mut numbers = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                numbers.extend(iter.by_ref().cloned());
+                break;
+            }
+            "-h" | "--exponents" => exponents = true,
+            // Negative numbers are reported as invalid numbers below
+            option
+                if option.len() > 1
+                    && option.starts_with('-')
+                    && !option[1..].starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                outcome.stderr = format!("factor: unrecognized option '{option}'\n").into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+            _ => numbers.push(arg.clone()),
+        }
+    }
+    if numbers.is_empty() {
+        let mut data = Vec::new();
+        if let Err(e) = stdin.read_to_end(&mut data) {
+            outcome.stderr = format!("factor: {}\n", io_message(&e)).into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+        numbers = String::from_utf8_lossy(&data)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+    }
+    for token in &numbers {
+        match line(token, exponents) {
+            Ok(text) => outcome.stdout.extend(text.as_bytes()),
+            Err(message) => {
+                outcome
+                    .stderr
+                    .extend(format!("factor: {message}\n").as_bytes());
+                outcome.status = 1;
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factor(args: &[&str], input: &str) -> (String, String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, &mut input.as_bytes());
+        (
+            String::from_utf8(outcome.stdout).unwrap(),
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    #[test]
+    fn factors_small_and_large_numbers() {
+        assert_eq!(factor(&["360"], "").0, "360: 2 2 2 3 3 5\n");
+        assert_eq!(factor(&["0", "1", "97"], "").0, "0:\n1:\n97: 97\n");
+        assert_eq!(
+            factor(&["18446744073709551615"], "").0,
+            "18446744073709551615: 3 5 17 257 641 65537 6700417\n"
+        );
+        assert_eq!(
+            factor(&["999999000001"], "").0,
+            "999999000001: 999999000001\n"
+        );
+        // Two primes above the trial division bound, split by rho
+        assert_eq!(
+            factor(&["1000036000099"], "").0,
+            "1000036000099: 1000003 1000033\n"
+        );
+    }
+
+    #[test]
+    fn reads_standard_input_and_exponents() {
+        assert_eq!(factor(&[], "12\n 15").0, "12: 2 2 3\n15: 3 5\n");
+        assert_eq!(factor(&["-h", "360"], "").0, "360: 2^3 3^2 5\n");
+    }
+
+    #[test]
+    fn reports_invalid_numbers() {
+        let (out, err, status) = factor(&["x", "6", "18446744073709551616", "-3"], "");
+        assert_eq!(out, "6: 2 3\n");
+        assert_eq!(
+            err,
+            "factor: 'x' is not a valid positive integer\n\
+             factor: '18446744073709551616' is too large\n\
+             factor: '-3' is not a valid positive integer\n"
+        );
+        assert_eq!(status, 1);
+    }
+}
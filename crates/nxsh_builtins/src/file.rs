@@ -0,0 +1,379 @@
+//! `file` builtin - determine file types
+//!
+//! Syntax:
+//!   file [-bhiL] [--mime-type] [--mime-encoding] [-F SEP] [-f NAMEFILE] FILE...
+//!
+//! Each FILE is classified by what it is on disk and, for regular files, by
+//! the magic numbers at its start rather than its name: executables,
+//! archives, images, media, documents, scripts by their `#!` line and text
+//! by its encoding. `-` reads standard input. `-b` leaves off the file
+//! names, `--mime-type` prints a MIME type instead of a description, `-i`
+//! adds the character set and `--mime-encoding` prints only that. Symbolic
+//! links are described unless `-L` follows them. `-f` reads further names,
+//! one per line, from NAMEFILE and `-F` replaces the `:` after each name.
+//!
+//! Detection is shared with completion and `ls` through `nxsh_hal::magic`.
+//!
+//! The exit status is 0 on success and 1 when any FILE could not be read.
+
+use crate::common::{hal_message, io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{FileMetadata, FileSystem};
+use nxsh_hal::magic::{self, FileClass, Magic, SNIFF_LEN};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The `file` builtin command implementation
+pub struct FileCommand;
+
+impl Builtin for FileCommand {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Determine file types"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the type of each FILE, found from its magic numbers rather than its name, \
+         as a description or a MIME type."
+    }
+
+    fn usage(&self) -> &'static str {
+        "file [-bhiL] [--mime-type] [--mime-encoding] [-F SEP] [-f NAMEFILE] FILE..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Determine file types. Use 'file -b --mime-type FILE' for just the MIME type."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run file for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Which part of the result is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Show {
+    Description,
+    MimeType,
+    Mime,
+    Encoding,
+}
+
+#[derive(Debug)]
+struct Options {
+    brief: bool,
+    show: Show,
+    dereference: bool,
+    separator: String,
+    name_files: Vec<String>,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            brief: false,
+            show: Show::Description,
+            dereference: false,
+            separator: ":".to_string(),
+            name_files: Vec::new(),
+            files: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--brief" => options.brief = true,
+                "--mime" => options.show = Show::Mime,
+                "--mime-type" => options.show = Show::MimeType,
+                "--mime-encoding" => options.show = Show::Encoding,
+                "--dereference" => options.dereference = true,
+                "--no-dereference" => options.dereference = false,
+                "--separator" | "--files-from" => {
+                    let value = iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '{arg}' requires an argument"))?;
+                    match arg.as_str() {
+                        "--separator" => options.separator = value,
+                        _ => options.name_files.push(value),
+                    }
+                }
+                long if long.starts_with("--separator=") => {
+                    options.separator = long["--separator=".len()..].to_string();
+                }
+                long if long.starts_with("--files-from=") => {
+                    options
+                        .name_files
+                        .push(long["--files-from=".len()..].to_string());
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'b' => options.brief = true,
+                            'i' => options.show = Show::Mime,
+                            'L' => options.dereference = true,
+                            'h' => options.dereference = false,
+                            'F' | 'f' => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached.to_string(),
+                                };
+                                match flag {
+                                    'F' => options.separator = value,
+                                    _ => options.name_files.push(value),
+                                }
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// A result for something that is not a regular file
+fn inode(description: impl Into<String>, mime: &'static str) -> Magic {
+    Magic {
+        description: description.into(),
+        mime,
+        charset: "binary",
+        class: FileClass::Data,
+    }
+}
+
+/// What kind of file system object METADATA describes, when it is not a
+/// regular file
+fn special(metadata: &FileMetadata) -> Option<Magic> {
+    if metadata.is_dir {
+        return Some(inode("directory", "inode/directory"));
+    }
+    #[cfg(unix)]
+    {
+        match metadata.mode & 0o170000 {
+            0o060000 => return Some(inode("block special", "inode/blockdevice")),
+            0o020000 => return Some(inode("character special", "inode/chardevice")),
+            0o010000 => return Some(inode("fifo (named pipe)", "inode/fifo")),
+            0o140000 => return Some(inode("socket", "inode/socket")),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Classify the file NAME, or say why it could not be read
+fn classify(fs: &FileSystem, cwd: &Path, name: &str, dereference: bool) -> Result<Magic, String> {
+    let path = cwd.join(name);
+    let metadata = if dereference {
+        fs.metadata(&path)
+    } else {
+        fs.symlink_metadata(&path)
+    };
+    let metadata = metadata.map_err(|e| hal_message(&e))?;
+    if metadata.is_symlink {
+        let target = fs.read_link(&path).map_err(|e| hal_message(&e))?;
+        let broken = if fs.metadata(&path).is_err() {
+            "broken "
+        } else {
+            ""
+        };
+        return Ok(inode(
+            format!("{broken}symbolic link to {}", target.display()),
+            "inode/symlink",
+        ));
+    }
+    if let Some(kind) = special(&metadata) {
+        return Ok(kind);
+    }
+    magic::identify_path(&path).map_err(|e| hal_message(&e))
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let mut options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("file: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    for name_file in &options.name_files {
+        match std::fs::read_to_string(cwd.join(name_file)) {
+            Ok(text) => options
+                .files
+                .extend(text.lines().filter(|l| !l.is_empty()).map(String::from)),
+            Err(e) => {
+                let message = format!("file: cannot open '{name_file}': {}\n", io_message(&e));
+                outcome.stderr.extend(message.as_bytes());
+                outcome.status = 1;
+            }
+        }
+    }
+    if options.files.is_empty() {
+        if options.name_files.is_empty() {
+            outcome.stderr = b"file: missing operand\n".to_vec();
+            outcome.status = 1;
+        }
+        return outcome;
+    }
+    let fs = match FileSystem::new() {
+        Ok(fs) => fs,
+        Err(e) => {
+            outcome.stderr = format!("file: {}\n", hal_message(&e)).into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+
+    let shown = |name: &str| match name {
+        "-" => "/dev/stdin".to_string(),
+        _ => name.to_string(),
+    };
+    let width = options
+        .files
+        .iter()
+        .map(|name| shown(name).chars().count())
+        .max()
+        .unwrap_or(0);
+    for name in &options.files {
+        let result = if name == "-" {
+            let mut head = Vec::with_capacity(SNIFF_LEN);
+            (&mut *stdin)
+                .take(SNIFF_LEN as u64)
+                .read_to_end(&mut head)
+                .map(|_| magic::identify(&head))
+                .map_err(|e| io_message(&e))
+        } else {
+            classify(&fs, cwd, name, options.dereference)
+        };
+        let text = match result {
+            Ok(magic) => match options.show {
+                Show::Description => magic.description,
+                Show::MimeType => magic.mime.to_string(),
+                Show::Mime => format!("{}; charset={}", magic.mime, magic.charset),
+                Show::Encoding => magic.charset.to_string(),
+            },
+            Err(message) => {
+                outcome.status = 1;
+                format!("cannot open `{name}' ({message})")
+            }
+        };
+        if options.brief {
+            outcome.stdout.extend(format!("{text}\n").as_bytes());
+        } else {
+            let name = shown(name);
+            let pad = width - name.chars().count();
+            let line = format!("{name}{}{:pad$} {text}\n", options.separator, "");
+            outcome.stdout.extend(line.as_bytes());
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(args: &[&str], dir: &Path, input: &str) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut input.as_bytes());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn describes_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("notes"), "plain words\n").unwrap();
+        std::fs::write(root.join("run"), "#!/bin/bash\necho hi\n").unwrap();
+        std::fs::write(root.join("empty"), "").unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+
+        assert_eq!(
+            file(&["notes", "run", "sub"], root, "").0,
+            "notes: ASCII text\n\
+             run:   Bourne-Again shell script, ASCII text executable\n\
+             sub:   directory\n"
+        );
+        assert_eq!(file(&["-b", "empty"], root, "").0, "empty\n");
+        assert_eq!(
+            file(&["-b", "--mime-type", "notes", "sub"], root, "").0,
+            "text/plain\ninode/directory\n"
+        );
+        assert_eq!(
+            file(&["-i", "-F", " -", "notes"], root, "").0,
+            "notes - text/plain; charset=us-ascii\n"
+        );
+        assert_eq!(
+            file(&["-"], root, "GIF89a\x01\0\x01\0").0,
+            "/dev/stdin: GIF image data, version 89a, 1 x 1\n"
+        );
+
+        let (out, status) = file(&["missing"], root, "");
+        assert_eq!(status, 1);
+        assert!(out.starts_with("missing: cannot open `missing' ("));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn describes_links_unless_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("target"), "text\n").unwrap();
+        std::os::unix::fs::symlink("target", root.join("link")).unwrap();
+        std::os::unix::fs::symlink("nowhere", root.join("dangling")).unwrap();
+
+        assert_eq!(
+            file(&["-b", "link"], root, "").0,
+            "symbolic link to target\n"
+        );
+        assert_eq!(file(&["-bL", "link"], root, "").0, "ASCII text\n");
+        assert_eq!(
+            file(&["-b", "dangling"], root, "").0,
+            "broken symbolic link to nowhere\n"
+        );
+    }
+}
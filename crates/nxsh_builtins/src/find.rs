@@ -0,0 +1,1415 @@
+//! `find` builtin - search for files in a directory hierarchy
+//!
+//! Syntax:
+//!   find [-H | -L | -P] [PATH...] [EXPRESSION]
+//!
+//! Each PATH (`.` by default) is walked depth first, and the expression is
+//! evaluated for every file met, from left to right with short-circuiting:
+//! `A B` and `A -a B` run B only when A is true, `A -o B` only when A is
+//! false, and `A , B` always. `!`/`-not` negates and `( ... )` groups. When
+//! the expression has no action other than `-prune` or `-quit`, `-print` is
+//! added to it.
+//!
+//! Tests: `-name -iname -path -ipath -regex -iregex -type -size -empty
+//! -mtime -atime -ctime -mmin -amin -cmin -newer -anewer -cnewer -perm
+//! -user -group -uid -gid -links -inum -readable -writable -executable
+//! -true -false`. Actions: `-print -print0 -printf -exec -execdir -delete
+//! -prune -quit`. Options: `-maxdepth -mindepth -depth -xdev -follow`.
+//! `-regex` takes POSIX extended regular expressions, which must match the
+//! whole path.
+//!
+//! Symbolic links are not followed by default (`-P`); `-H` follows the ones
+//! named as PATHs and `-L` all of them, reporting loops. Directory entries
+//! are visited in name order.
+
+use crate::common::{hal_message, io_message};
+use crate::grep::translate;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{DirectoryHandle, FileMetadata, FileSystem};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The `find` builtin command implementation
+pub struct FindCommand;
+
+impl Builtin for FindCommand {
+    fn name(&self) -> &'static str {
+        "find"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Search for files in a directory hierarchy"
+    }
+
+    fn description(&self) -> &'static str {
+        "Walk each PATH and evaluate the expression of tests, actions and operators \
+         for every file, printing the ones it matches by default."
+    }
+
+    fn usage(&self) -> &'static str {
+        "find [-H | -L | -P] [PATH...] [EXPRESSION]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Search for files in a directory hierarchy. Use 'find . -name \"*.rs\" -type f'."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let find = match Find::parse(args, &ctx.cwd) {
+            Ok(find) => find,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("find: {message}\n").into_bytes()))
+            }
+        };
+        let outcome = find.run(ctx);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Which symbolic links are followed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    /// `-P`: none
+    Never,
+    /// `-H`: the starting points
+    Roots,
+    /// `-L`: all
+    Always,
+}
+
+/// A numeric test argument: `+N` is more than N, `-N` less and `N` exactly
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Less(f64),
+    Exactly(f64),
+    More(f64),
+}
+
+impl Number {
+    fn parse(text: &str) -> Option<Self> {
+        let (make, digits): (fn(f64) -> Number, &str) = match text.as_bytes().first() {
+            Some(b'+') => (Number::More, &text[1..]),
+            Some(b'-') => (Number::Less, &text[1..]),
+            _ => (Number::Exactly, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok().map(make)
+    }
+
+    fn matches(self, n: f64) -> bool {
+        match self {
+            Number::Less(limit) => n < limit,
+            Number::Exactly(value) => n == value,
+            Number::More(limit) => n > limit,
+        }
+    }
+}
+
+/// The timestamp a time test looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stamp {
+    Modified,
+    Accessed,
+    Changed,
+}
+
+/// How `-perm` compares mode bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PermMatch {
+    /// `MODE`: exactly these bits
+    Exact,
+    /// `-MODE`: at least these bits
+    All,
+    /// `/MODE`: any of these bits
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug)]
+enum Test {
+    Name(glob::Pattern, bool),
+    Path(glob::Pattern, bool),
+    Regex(Regex),
+    Type(Vec<char>),
+    /// Size in units of the given number of bytes, rounded up
+    Size(Number, u64),
+    /// Age in whole periods of the given number of seconds
+    Age(Stamp, Number, f64),
+    Newer(Stamp, SystemTime),
+    Empty,
+    Perm(PermMatch, u32),
+    User(u32),
+    Group(u32),
+    Links(Number),
+    Inode(Number),
+    Access(Access),
+    True,
+    False,
+}
+
+/// One piece of a `-printf` format
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Directive(char),
+    /// `%A@`, `%C@` and `%T@`: a timestamp in seconds since the epoch
+    Epoch(Stamp),
+}
+
+#[derive(Debug)]
+enum Action {
+    Print,
+    Print0,
+    Printf(Vec<Piece>),
+    /// An index into the `-exec` and `-execdir` commands
+    Exec(usize),
+    Delete,
+    Prune,
+    Quit,
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// `A , B`: both, with the value of B
+    List(Box<Expr>, Box<Expr>),
+    Test(Test),
+    Action(Action),
+}
+
+/// A command run by `-exec` or `-execdir`
+#[derive(Debug)]
+struct Exec {
+    argv: Vec<String>,
+    /// `{} +`: many files per command
+    batch: bool,
+    /// `-execdir`: run from the file's directory on `./NAME`
+    in_dir: bool,
+    pending: Vec<String>,
+    pending_dir: PathBuf,
+    pending_bytes: usize,
+}
+
+/// Batched arguments beyond this many bytes start a new command
+const BATCH_BYTES: usize = 128 * 1024;
+
+#[derive(Debug)]
+struct Find {
+    follow: Follow,
+    roots: Vec<String>,
+    expr: Expr,
+    execs: Vec<Exec>,
+    min_depth: usize,
+    max_depth: usize,
+    depth_first: bool,
+    same_device: bool,
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Parser over the expression arguments
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+    cwd: &'a Path,
+    find: Find,
+    has_action: bool,
+}
+
+impl Find {
+    fn parse(args: &[String], cwd: &Path) -> Result<Self, String> {
+        let mut follow = Follow::Never;
+        let mut pos = 0;
+        while let Some(arg) = args.get(pos) {
+            match arg.as_str() {
+                "-H" => follow = Follow::Roots,
+                "-L" => follow = Follow::Always,
+                "-P" => follow = Follow::Never,
+                "--" => {
+                    pos += 1;
+                    break;
+                }
+                _ => break,
+            }
+            pos += 1;
+        }
+        let start = pos;
+        while args.get(pos).is_some_and(|arg| !is_expression_start(arg)) {
+            pos += 1;
+        }
+        let mut roots = args[start..pos].to_vec();
+        if roots.is_empty() {
+            roots.push(".".to_string());
+        }
+
+        let mut parser = Parser {
+            args: &args[pos..],
+            pos: 0,
+            cwd,
+            find: Find {
+                follow,
+                roots,
+                expr: Expr::Test(Test::True),
+                execs: Vec::new(),
+                min_depth: 0,
+                max_depth: usize::MAX,
+                depth_first: false,
+                same_device: false,
+            },
+            has_action: false,
+        };
+        let expr = if parser.args.is_empty() {
+            None
+        } else {
+            let expr = parser.list()?;
+            if let Some(extra) = parser.args.get(parser.pos) {
+                return Err(match extra.as_str() {
+                    ")" => "invalid expression; you have too many ')'".to_string(),
+                    other => format!("paths must precede expression: `{other}'"),
+                });
+            }
+            Some(expr)
+        };
+        let mut find = parser.find;
+        find.expr = match (expr, parser.has_action) {
+            (Some(expr), true) => expr,
+            (Some(expr), false) => Expr::And(Box::new(expr), Box::new(Expr::Action(Action::Print))),
+            (None, _) => Expr::Action(Action::Print),
+        };
+        Ok(find)
+    }
+}
+
+/// Whether ARG begins the expression rather than naming a path
+fn is_expression_start(arg: &str) -> bool {
+    (arg.starts_with('-') && arg.len() > 1) || arg == "(" || arg == "!" || arg == ")" || arg == ","
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(String::as_str)
+    }
+
+    fn list(&mut self) -> Result<Expr, String> {
+        let mut left = self.or()?;
+        while self.peek() == Some(",") {
+            self.pos += 1;
+            left = Expr::List(Box::new(left), Box::new(self.or()?));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while matches!(self.peek(), Some("-o" | "-or")) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some("-a" | "-and") => self.pos += 1,
+                None | Some(")" | "-o" | "-or" | ",") => return Ok(left),
+                Some(_) => {}
+            }
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            None => Err("invalid expression; expected an expression at the end".to_string()),
+            Some("!" | "-not") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                if self.peek() == Some(")") {
+                    return Err("invalid expression; empty parentheses are not allowed".to_string());
+                }
+                let expr = self.list()?;
+                if self.peek() != Some(")") {
+                    return Err("invalid expression; I was expecting to find a ')' somewhere but did not see one".to_string());
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(")") => Err("invalid expression; you have too many ')'".to_string()),
+            Some(op @ ("-a" | "-and" | "-o" | "-or" | ",")) => Err(format!(
+                "invalid expression; you have used a binary operator '{op}' with nothing before it"
+            )),
+            Some(_) => self.primary(),
+        }
+    }
+
+    /// The argument of the predicate just read
+    fn value(&mut self, predicate: &str) -> Result<String, String> {
+        let value = self
+            .args
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| format!("missing argument to `{predicate}'"))?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn number(&mut self, predicate: &str) -> Result<Number, String> {
+        let value = self.value(predicate)?;
+        Number::parse(&value).ok_or_else(|| format!("invalid argument `{value}' to `{predicate}'"))
+    }
+
+    fn depth(&mut self, predicate: &str) -> Result<usize, String> {
+        let value = self.value(predicate)?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid argument `{value}' to `{predicate}'"))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let predicate = self.args[self.pos].clone();
+        self.pos += 1;
+        let test = match predicate.as_str() {
+            "-name" | "-iname" | "-path" | "-ipath" | "-wholename" | "-iwholename" => {
+                let value = self.value(&predicate)?;
+                let pattern = glob::Pattern::new(&value)
+                    .map_err(|e| format!("invalid pattern `{value}': {}", e.msg))?;
+                let fold = predicate.starts_with("-i");
+                if predicate.ends_with("name") && !predicate.ends_with("wholename") {
+                    Test::Name(pattern, fold)
+                } else {
+                    Test::Path(pattern, fold)
+                }
+            }
+            "-regex" | "-iregex" => {
+                let value = self.value(&predicate)?;
+                let source = translate(&value, true)?;
+                let flags = if predicate == "-iregex" {
+                    "(?is)"
+                } else {
+                    "(?s)"
+                };
+                Test::Regex(
+                    Regex::new(&format!("{flags}^(?:{source})$"))
+                        .map_err(|e| format!("invalid regular expression `{value}': {e}"))?,
+                )
+            }
+            "-type" => {
+                let value = self.value(&predicate)?;
+                let mut kinds = Vec::new();
+                for kind in value.split(',') {
+                    match kind {
+                        "f" | "d" | "l" | "b" | "c" | "p" | "s" => kinds.extend(kind.chars()),
+                        _ => return Err(format!("Unknown argument to -type: {kind}")),
+                    }
+                }
+                Test::Type(kinds)
+            }
+            "-size" => {
+                let value = self.value(&predicate)?;
+                let (digits, unit) = match value.char_indices().last() {
+                    Some((at, c)) if c.is_ascii_alphabetic() => (&value[..at], Some(c)),
+                    _ => (value.as_str(), None),
+                };
+                let unit = match unit {
+                    None | Some('b') => 512,
+                    Some('c') => 1,
+                    Some('w') => 2,
+                    Some('k') => 1024,
+                    Some('M') => 1024 * 1024,
+                    Some('G') => 1024 * 1024 * 1024,
+                    Some(_) => return Err(format!("invalid -size type `{value}'")),
+                };
+                let number = Number::parse(digits)
+                    .ok_or_else(|| format!("invalid argument `{value}' to `-size'"))?;
+                Test::Size(number, unit)
+            }
+            "-mtime" | "-atime" | "-ctime" | "-mmin" | "-amin" | "-cmin" => {
+                let number = self.number(&predicate)?;
+                let period = if predicate.ends_with("min") {
+                    60.0
+                } else {
+                    86400.0
+                };
+                Test::Age(stamp_of(&predicate), number, period)
+            }
+            "-newer" | "-anewer" | "-cnewer" => {
+                let value = self.value(&predicate)?;
+                let metadata = FileSystem::new()
+                    .and_then(|fs| fs.metadata(self.cwd.join(&value)))
+                    .map_err(|e| format!("'{value}': {}", hal_message(&e)))?;
+                let time = timestamp(&metadata, Stamp::Modified).unwrap_or(SystemTime::UNIX_EPOCH);
+                Test::Newer(stamp_of(&predicate), time)
+            }
+            "-empty" => Test::Empty,
+            "-perm" => {
+                let value = self.value(&predicate)?;
+                let (how, mode) = match value.as_bytes().first() {
+                    Some(b'-') => (PermMatch::All, &value[1..]),
+                    Some(b'/') => (PermMatch::Any, &value[1..]),
+                    _ => (PermMatch::Exact, value.as_str()),
+                };
+                let bits = parse_mode(mode).ok_or_else(|| format!("invalid mode `{value}'"))?;
+                Test::Perm(how, bits)
+            }
+            "-user" | "-group" => {
+                let value = self.value(&predicate)?;
+                let file = if predicate == "-user" {
+                    "/etc/passwd"
+                } else {
+                    "/etc/group"
+                };
+                let id = value
+                    .parse()
+                    .ok()
+                    .or_else(|| account_id(file, &value))
+                    .ok_or_else(|| {
+                        format!("`{value}' is not the name of a known {}", &predicate[1..])
+                    })?;
+                if predicate == "-user" {
+                    Test::User(id)
+                } else {
+                    Test::Group(id)
+                }
+            }
+            "-uid" | "-gid" => {
+                let value = self.value(&predicate)?;
+                let id = value
+                    .parse()
+                    .map_err(|_| format!("invalid argument `{value}' to `{predicate}'"))?;
+                if predicate == "-uid" {
+                    Test::User(id)
+                } else {
+                    Test::Group(id)
+                }
+            }
+            "-links" => Test::Links(self.number(&predicate)?),
+            "-inum" => Test::Inode(self.number(&predicate)?),
+            "-readable" => Test::Access(Access::Read),
+            "-writable" => Test::Access(Access::Write),
+            "-executable" => Test::Access(Access::Execute),
+            "-true" => Test::True,
+            "-false" => Test::False,
+            // Options apply to the whole walk wherever they appear
+            "-maxdepth" => {
+                self.find.max_depth = self.depth(&predicate)?;
+                Test::True
+            }
+            "-mindepth" => {
+                self.find.min_depth = self.depth(&predicate)?;
+                Test::True
+            }
+            "-depth" | "-d" => {
+                self.find.depth_first = true;
+                Test::True
+            }
+            "-xdev" | "-mount" => {
+                self.find.same_device = true;
+                Test::True
+            }
+            "-follow" => {
+                self.find.follow = Follow::Always;
+                Test::True
+            }
+            "-noleaf" | "-ignore_readdir_race" | "-nowarn" => Test::True,
+            _ => return self.action(predicate),
+        };
+        Ok(Expr::Test(test))
+    }
+
+    fn action(&mut self, predicate: String) -> Result<Expr, String> {
+        let action = match predicate.as_str() {
+            "-print" => Action::Print,
+            "-print0" => Action::Print0,
+            "-printf" => Action::Printf(parse_format(&self.value(&predicate)?)),
+            "-exec" | "-execdir" => {
+                let mut argv = Vec::new();
+                let batch = loop {
+                    let Some(arg) = self.args.get(self.pos) else {
+                        return Err(format!("missing argument to `{predicate}'"));
+                    };
+                    self.pos += 1;
+                    match arg.as_str() {
+                        ";" => break false,
+                        "+" if argv.last().is_some_and(|last: &String| last == "{}") => {
+                            argv.pop();
+                            break true;
+                        }
+                        _ => argv.push(arg.clone()),
+                    }
+                };
+                if argv.is_empty() {
+                    return Err(format!("missing argument to `{predicate}'"));
+                }
+                if batch && argv.iter().any(|arg| arg.contains("{}")) {
+                    return Err(format!(
+                        "only one instance of {{}} is supported with {predicate} ... +"
+                    ));
+                }
+                self.find.execs.push(Exec {
+                    argv,
+                    batch,
+                    in_dir: predicate == "-execdir",
+                    pending: Vec::new(),
+                    pending_dir: PathBuf::new(),
+                    pending_bytes: 0,
+                });
+                Action::Exec(self.find.execs.len() - 1)
+            }
+            "-delete" => {
+                // Contents have to go before their directory
+                self.find.depth_first = true;
+                Action::Delete
+            }
+            "-prune" => {
+                return Ok(Expr::Action(Action::Prune));
+            }
+            "-quit" => {
+                return Ok(Expr::Action(Action::Quit));
+            }
+            other if other.starts_with('-') => {
+                return Err(format!("unknown predicate `{other}'"));
+            }
+            other => return Err(format!("paths must precede expression: `{other}'")),
+        };
+        self.has_action = true;
+        Ok(Expr::Action(action))
+    }
+}
+
+fn stamp_of(predicate: &str) -> Stamp {
+    match predicate.as_bytes().get(1) {
+        Some(b'a') => Stamp::Accessed,
+        Some(b'c') => Stamp::Changed,
+        _ => Stamp::Modified,
+    }
+}
+
+fn timestamp(metadata: &FileMetadata, stamp: Stamp) -> Option<SystemTime> {
+    match stamp {
+        Stamp::Modified => metadata.modified,
+        Stamp::Accessed => metadata.accessed,
+        #[cfg(unix)]
+        Stamp::Changed => metadata.changed,
+        #[cfg(not(unix))]
+        Stamp::Changed => metadata.created,
+    }
+}
+
+/// Parse an octal mode, or a symbolic one like `u+x,go=r`
+fn parse_mode(text: &str) -> Option<u32> {
+    if !text.is_empty() && text.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return u32::from_str_radix(text, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777);
+    }
+    let mut mode = 0;
+    for clause in text.split(',') {
+        let split = clause.find(['+', '-', '='])?;
+        let (who, rest) = clause.split_at(split);
+        let mut mask = 0;
+        for c in who.chars() {
+            mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return None,
+            };
+        }
+        if mask == 0 {
+            mask = 0o7777;
+        }
+        let op = rest.chars().next()?;
+        let mut bits = 0;
+        for c in rest[1..].chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return None,
+            };
+        }
+        match op {
+            '+' => mode |= bits & mask,
+            '-' => mode &= !(bits & mask),
+            _ => mode = (mode & !mask) | (bits & mask),
+        }
+    }
+    Some(mode)
+}
+
+/// The id of account NAME in a passwd or group FILE
+pub(crate) fn account_id(file: &str, name: &str) -> Option<u32> {
+    let content = std::fs::read_to_string(file).ok()?;
+    content.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        (fields.next() == Some(name))
+            .then(|| fields.nth(1)?.parse().ok())
+            .flatten()
+    })
+}
+
+/// The name of account ID in a passwd or group FILE, if it has one
+pub(crate) fn account(file: &str, id: u32) -> Option<String> {
+    let content = std::fs::read_to_string(file).ok()?;
+    content.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.get(2) == Some(&id.to_string().as_str())).then(|| fields[0].to_string())
+    })
+}
+
+/// The name of account ID in a passwd or group FILE, or the number itself
+fn account_name(file: &str, id: u32) -> String {
+    account(file, id).unwrap_or_else(|| id.to_string())
+}
+
+/// Split a `-printf` format into text and directives
+fn parse_format(format: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('0') => text.push('\0'),
+                Some('\\') => text.push('\\'),
+                Some(other) => {
+                    text.push('\\');
+                    text.push(other);
+                }
+                None => text.push('\\'),
+            },
+            '%' => {
+                let piece = match chars.next() {
+                    Some('%') => {
+                        text.push('%');
+                        continue;
+                    }
+                    Some(stamp @ ('A' | 'C' | 'T')) if chars.peek() == Some(&'@') => {
+                        chars.next();
+                        Piece::Epoch(match stamp {
+                            'A' => Stamp::Accessed,
+                            'C' => Stamp::Changed,
+                            _ => Stamp::Modified,
+                        })
+                    }
+                    Some(directive) => Piece::Directive(directive),
+                    None => {
+                        text.push('%');
+                        continue;
+                    }
+                };
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(piece);
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+/// The last component of a path as `find` shows it: `dir/` is `dir`, `/`
+/// stays `/`
+fn base_name(display: &str) -> &str {
+    let trimmed = display.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return if display.is_empty() { "" } else { "/" };
+    }
+    trimmed.rsplit('/').next().unwrap_or(trimmed)
+}
+
+/// The directory part of a path, `.` when it has none
+fn dir_name(display: &str) -> &str {
+    let trimmed = display.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(0) => "/",
+        Some(at) => &trimmed[..at],
+        None => ".",
+    }
+}
+
+/// One visited file
+struct Entry<'e> {
+    display: &'e str,
+    path: &'e Path,
+    root: &'e str,
+    metadata: &'e FileMetadata,
+    depth: usize,
+}
+
+/// Walk state while the expression runs
+struct Walker<'a> {
+    find: Find,
+    ctx: &'a ShellContext,
+    fs: FileSystem,
+    now: SystemTime,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+    prune: bool,
+    quit: bool,
+}
+
+impl Find {
+    fn run(self, ctx: &ShellContext) -> Outcome {
+        let fs = match FileSystem::new() {
+            Ok(fs) => fs,
+            Err(e) => {
+                return Outcome {
+                    stdout: Vec::new(),
+                    stderr: format!("find: {e}\n").into_bytes(),
+                    status: 1,
+                }
+            }
+        };
+        let roots = self.roots.clone();
+        let mut walker = Walker {
+            find: self,
+            ctx,
+            fs,
+            now: SystemTime::now(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+            prune: false,
+            quit: false,
+        };
+        for root in &roots {
+            if walker.quit {
+                break;
+            }
+            let path = ctx.cwd.join(root);
+            walker.visit(root, &path, root, 0, None, &mut Vec::new());
+        }
+        walker.flush_all();
+        Outcome {
+            stdout: walker.stdout,
+            stderr: walker.stderr,
+            status: walker.status,
+        }
+    }
+}
+
+impl Walker<'_> {
+    fn error(&mut self, message: String) {
+        self.stderr
+            .extend(format!("find: {message}\n").into_bytes());
+        self.status = 1;
+    }
+
+    fn visit(
+        &mut self,
+        display: &str,
+        path: &Path,
+        root: &str,
+        depth: usize,
+        device: Option<u64>,
+        ancestors: &mut Vec<(u64, u64, String)>,
+    ) {
+        let follow = match self.find.follow {
+            Follow::Never => false,
+            Follow::Roots => depth == 0,
+            Follow::Always => true,
+        };
+        let metadata = if follow {
+            // A dangling link is reported as the link itself
+            self.fs
+                .metadata(path)
+                .or_else(|_| self.fs.symlink_metadata(path))
+        } else {
+            self.fs.symlink_metadata(path)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let message = hal_message(&e);
+                self.error(format!("'{display}': {message}"));
+                return;
+            }
+        };
+
+        #[cfg(unix)]
+        let identity = (metadata.device, metadata.inode);
+        #[cfg(not(unix))]
+        let identity = (0, 0);
+        if metadata.is_dir && cfg!(unix) && follow {
+            if let Some((_, _, earlier)) = ancestors
+                .iter()
+                .find(|(dev, ino, _)| (*dev, *ino) == identity)
+            {
+                let earlier = earlier.clone();
+                self.error(format!(
+                    "File system loop detected; '{display}' is part of the same file system loop as '{earlier}'."
+                ));
+                return;
+            }
+        }
+        let device = device.unwrap_or(identity.0);
+        let entry = Entry {
+            display,
+            path,
+            root,
+            metadata: &metadata,
+            depth,
+        };
+        let selected = depth >= self.find.min_depth;
+        self.prune = false;
+        if selected && !self.find.depth_first {
+            self.evaluate(&entry);
+        }
+        let descend = metadata.is_dir
+            && depth < self.find.max_depth
+            && !(self.prune && !self.find.depth_first)
+            && !self.quit
+            && !(self.find.same_device && identity.0 != device);
+        if descend {
+            match DirectoryHandle::open(path).and_then(|dir| dir.read_dir()) {
+                Ok(mut children) => {
+                    children.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+                    ancestors.push((identity.0, identity.1, display.to_string()));
+                    for child in children {
+                        if self.quit {
+                            break;
+                        }
+                        let name = child.file_name.to_string_lossy();
+                        let child_display = if display.ends_with('/') {
+                            format!("{display}{name}")
+                        } else {
+                            format!("{display}/{name}")
+                        };
+                        let child_path = path.join(&child.file_name);
+                        self.visit(
+                            &child_display,
+                            &child_path,
+                            root,
+                            depth + 1,
+                            Some(device),
+                            ancestors,
+                        );
+                    }
+                    ancestors.pop();
+                }
+                Err(e) => {
+                    let message = hal_message(&e);
+                    self.error(format!("'{display}': {message}"));
+                }
+            }
+        }
+        if selected && self.find.depth_first && !self.quit {
+            self.evaluate(&entry);
+        }
+    }
+
+    fn evaluate(&mut self, entry: &Entry) {
+        let expr = std::mem::replace(&mut self.find.expr, Expr::Test(Test::True));
+        self.eval(&expr, entry);
+        self.find.expr = expr;
+    }
+
+    fn eval(&mut self, expr: &Expr, entry: &Entry) -> bool {
+        match expr {
+            Expr::And(left, right) => self.eval(left, entry) && self.eval(right, entry),
+            Expr::Or(left, right) => self.eval(left, entry) || self.eval(right, entry),
+            Expr::Not(inner) => !self.eval(inner, entry),
+            Expr::List(left, right) => {
+                self.eval(left, entry);
+                self.eval(right, entry)
+            }
+            Expr::Test(test) => self.test(test, entry),
+            Expr::Action(action) => self.act(action, entry),
+        }
+    }
+
+    fn test(&mut self, test: &Test, entry: &Entry) -> bool {
+        let metadata = entry.metadata;
+        let options = |fold: bool| glob::MatchOptions {
+            case_sensitive: !fold,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        match test {
+            Test::Name(pattern, fold) => {
+                pattern.matches_with(base_name(entry.display), options(*fold))
+            }
+            Test::Path(pattern, fold) => pattern.matches_with(entry.display, options(*fold)),
+            Test::Regex(regex) => regex.is_match(entry.display),
+            Test::Type(kinds) => kinds.contains(&type_char(metadata)),
+            Test::Size(number, unit) => number.matches(metadata.size.div_ceil(*unit) as f64),
+            Test::Age(stamp, number, period) => match timestamp(metadata, *stamp) {
+                Some(time) => {
+                    let age = match self.now.duration_since(time) {
+                        Ok(age) => age.as_secs_f64(),
+                        Err(e) => -e.duration().as_secs_f64(),
+                    };
+                    number.matches((age / period).floor())
+                }
+                None => false,
+            },
+            Test::Newer(stamp, reference) => {
+                timestamp(metadata, *stamp).is_some_and(|time| time > *reference)
+            }
+            Test::Empty => {
+                if metadata.is_dir {
+                    DirectoryHandle::open(entry.path)
+                        .and_then(|dir| dir.read_dir())
+                        .is_ok_and(|children| children.is_empty())
+                } else {
+                    metadata.is_file && metadata.size == 0
+                }
+            }
+            Test::Perm(how, bits) => {
+                let mode = mode_bits(metadata) & 0o7777;
+                match how {
+                    PermMatch::Exact => mode == *bits,
+                    PermMatch::All => mode & bits == *bits,
+                    PermMatch::Any => *bits == 0 || mode & bits != 0,
+                }
+            }
+            #[cfg(unix)]
+            Test::User(uid) => metadata.uid == *uid,
+            #[cfg(unix)]
+            Test::Group(gid) => metadata.gid == *gid,
+            #[cfg(unix)]
+            Test::Links(number) => number.matches(metadata.nlink as f64),
+            #[cfg(unix)]
+            Test::Inode(number) => number.matches(metadata.inode as f64),
+            #[cfg(not(unix))]
+            Test::User(_) | Test::Group(_) | Test::Links(_) | Test::Inode(_) => false,
+            Test::Access(access) => accessible(entry.path, metadata, *access),
+            Test::True => true,
+            Test::False => false,
+        }
+    }
+
+    fn act(&mut self, action: &Action, entry: &Entry) -> bool {
+        match action {
+            Action::Print => {
+                self.stdout.extend(entry.display.as_bytes());
+                self.stdout.push(b'\n');
+                true
+            }
+            Action::Print0 => {
+                self.stdout.extend(entry.display.as_bytes());
+                self.stdout.push(0);
+                true
+            }
+            Action::Printf(pieces) => {
+                let text = self.format(pieces, entry);
+                self.stdout.extend(text.into_bytes());
+                true
+            }
+            Action::Exec(index) => self.exec(*index, entry),
+            Action::Delete => {
+                if base_name(entry.display) == "." {
+                    return true;
+                }
+                let result = if entry.metadata.is_dir {
+                    std::fs::remove_dir(entry.path).map_err(|e| io_message(&e))
+                } else {
+                    self.fs.remove_file(entry.path).map_err(|e| hal_message(&e))
+                };
+                match result {
+                    Ok(()) => true,
+                    Err(message) => {
+                        self.error(format!("cannot delete '{}': {message}", entry.display));
+                        false
+                    }
+                }
+            }
+            Action::Prune => {
+                self.prune = true;
+                true
+            }
+            Action::Quit => {
+                self.quit = true;
+                true
+            }
+        }
+    }
+
+    fn format(&self, pieces: &[Piece], entry: &Entry) -> String {
+        let metadata = entry.metadata;
+        let mut out = String::new();
+        for piece in pieces {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Epoch(stamp) => {
+                    let seconds = timestamp(metadata, *stamp)
+                        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                        .map_or(0.0, |d| d.as_secs_f64());
+                    out.push_str(&format!("{seconds:.10}"));
+                }
+                Piece::Directive(directive) => match directive {
+                    'p' => out.push_str(entry.display),
+                    'f' => out.push_str(base_name(entry.display)),
+                    'h' => out.push_str(dir_name(entry.display)),
+                    'P' => out.push_str(
+                        entry
+                            .display
+                            .strip_prefix(entry.root)
+                            .map_or(entry.display, |rest| rest.trim_start_matches('/')),
+                    ),
+                    'H' => out.push_str(entry.root),
+                    's' => out.push_str(&metadata.size.to_string()),
+                    'd' => out.push_str(&entry.depth.to_string()),
+                    'y' => out.push(type_char(metadata)),
+                    'm' => out.push_str(&format!("{:o}", mode_bits(metadata) & 0o7777)),
+                    #[cfg(unix)]
+                    'i' => out.push_str(&metadata.inode.to_string()),
+                    #[cfg(unix)]
+                    'n' => out.push_str(&metadata.nlink.to_string()),
+                    #[cfg(unix)]
+                    'U' => out.push_str(&metadata.uid.to_string()),
+                    #[cfg(unix)]
+                    'G' => out.push_str(&metadata.gid.to_string()),
+                    #[cfg(unix)]
+                    'u' => out.push_str(&account_name("/etc/passwd", metadata.uid)),
+                    #[cfg(unix)]
+                    'g' => out.push_str(&account_name("/etc/group", metadata.gid)),
+                    'l' => {
+                        if metadata.is_symlink {
+                            if let Ok(target) = self.fs.read_link(entry.path) {
+                                out.push_str(&target.to_string_lossy());
+                            }
+                        }
+                    }
+                    other => {
+                        out.push('%');
+                        out.push(*other);
+                    }
+                },
+            }
+        }
+        out
+    }
+
+    /// Run or queue a `-exec` command for ENTRY
+    fn exec(&mut self, index: usize, entry: &Entry) -> bool {
+        let (argument, dir) = if self.find.execs[index].in_dir {
+            let parent = entry.path.parent().unwrap_or(entry.path).to_path_buf();
+            (format!("./{}", base_name(entry.display)), parent)
+        } else {
+            (entry.display.to_string(), self.ctx.cwd.clone())
+        };
+        if !self.find.execs[index].batch {
+            let argv: Vec<String> = self.find.execs[index]
+                .argv
+                .iter()
+                .map(|arg| arg.replace("{}", &argument))
+                .collect();
+            return self.spawn(&argv, &dir);
+        }
+        let exec = &self.find.execs[index];
+        if !exec.pending.is_empty()
+            && (exec.pending_bytes + argument.len() > BATCH_BYTES || exec.pending_dir != dir)
+        {
+            self.flush(index);
+        }
+        let exec = &mut self.find.execs[index];
+        exec.pending_bytes += argument.len() + 1;
+        exec.pending.push(argument);
+        exec.pending_dir = dir;
+        true
+    }
+
+    /// Run the queued files of a `{} +` command
+    fn flush(&mut self, index: usize) {
+        let exec = &mut self.find.execs[index];
+        if exec.pending.is_empty() {
+            return;
+        }
+        let mut argv = exec.argv.clone();
+        argv.append(&mut exec.pending);
+        exec.pending_bytes = 0;
+        let dir = exec.pending_dir.clone();
+        if !self.spawn(&argv, &dir) {
+            self.status = 1;
+        }
+    }
+
+    fn flush_all(&mut self) {
+        for index in 0..self.find.execs.len() {
+            self.flush(index);
+        }
+    }
+
+    /// Run an external command in DIR, collecting its output; true when it
+    /// exits successfully
+    fn spawn(&mut self, argv: &[String], dir: &Path) -> bool {
+        let Some((program, args)) = argv.split_first() else {
+            return false;
+        };
+        let resolved = self
+            .ctx
+            .resolve_command(program)
+            .unwrap_or_else(|| program.into());
+        let mut command = std::process::Command::new(resolved);
+        command
+            .args(args)
+            .current_dir(dir)
+            .stdin(std::process::Stdio::null());
+        if let Ok(env) = self.ctx.env.read() {
+            for (key, value) in env.iter() {
+                command.env(key, value);
+            }
+        }
+        match command.output() {
+            Ok(output) => {
+                self.stdout.extend(output.stdout);
+                self.stderr.extend(output.stderr);
+                output.status.success()
+            }
+            Err(e) => {
+                self.error(format!("'{program}': {}", io_message(&e)));
+                false
+            }
+        }
+    }
+}
+
+/// The `-type` letter of a file
+fn type_char(metadata: &FileMetadata) -> char {
+    if metadata.is_symlink {
+        return 'l';
+    }
+    if metadata.is_dir {
+        return 'd';
+    }
+    if metadata.is_file {
+        return 'f';
+    }
+    #[cfg(unix)]
+    {
+        match metadata.mode & 0o170000 {
+            0o060000 => return 'b',
+            0o020000 => return 'c',
+            0o010000 => return 'p',
+            0o140000 => return 's',
+            _ => {}
+        }
+    }
+    'f'
+}
+
+/// Permission bits; elsewhere than Unix only the read-only flag is known
+fn mode_bits(metadata: &FileMetadata) -> u32 {
+    #[cfg(unix)]
+    {
+        metadata.mode
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.permissions.readonly() {
+            0o555
+        } else {
+            0o777
+        }
+    }
+}
+
+/// Whether the current user may access PATH, like `access(2)`
+fn accessible(path: &Path, metadata: &FileMetadata, access: Access) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let _ = metadata;
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mode = match access {
+            Access::Read => libc::R_OK,
+            Access::Write => libc::W_OK,
+            Access::Execute => libc::X_OK,
+        };
+        // SAFETY: `path` is a valid NUL-terminated string for the call
+        unsafe { libc::access(path.as_ptr(), mode) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        match access {
+            Access::Read => true,
+            Access::Write => !metadata.permissions.readonly(),
+            Access::Execute => metadata.is_dir,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn find(dir: &Path, list: &[&str]) -> (String, String, i32) {
+        let mut ctx = ShellContext::new();
+        ctx.cwd = dir.to_path_buf();
+        let find = Find::parse(&args(list), dir).unwrap();
+        let outcome = find.run(&ctx);
+        (
+            String::from_utf8(outcome.stdout).unwrap(),
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("README.md"), "readme\n").unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.path().join("src/nested/lib.rs"), vec![b'x'; 2000]).unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_numbers_and_modes() {
+        assert_eq!(Number::parse("+3"), Some(Number::More(3.0)));
+        assert_eq!(Number::parse("-3"), Some(Number::Less(3.0)));
+        assert_eq!(Number::parse("3"), Some(Number::Exactly(3.0)));
+        assert_eq!(Number::parse("3x"), None);
+        assert_eq!(parse_mode("755"), Some(0o755));
+        assert_eq!(parse_mode("u+x"), Some(0o100));
+        assert_eq!(parse_mode("a=r,u+w"), Some(0o644));
+        assert_eq!(parse_mode("q+r"), None);
+        assert_eq!(base_name("dir/sub/"), "sub");
+        assert_eq!(base_name("/"), "/");
+        assert_eq!(dir_name("file"), ".");
+        assert_eq!(dir_name("/file"), "/");
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        let cwd = Path::new(".");
+        for list in [
+            &["-name"][..],
+            &["(", "-true"],
+            &["-true", ")"],
+            &["-o", "-true"],
+            &["-bogus"],
+            &["-type", "x"],
+            &["-exec", "echo", "{}"],
+            &["-true", "path"],
+        ] {
+            assert!(Find::parse(&args(list), cwd).is_err(), "{list:?}");
+        }
+        let find = Find::parse(&args(&["a", "b", "-name", "x"]), cwd).unwrap();
+        assert_eq!(find.roots, ["a", "b"]);
+        assert!(
+            matches!(find.expr, Expr::And(_, ref print) if matches!(**print, Expr::Action(Action::Print)))
+        );
+    }
+
+    #[test]
+    fn evaluates_tests_with_short_circuit() {
+        let dir = tree();
+        let (out, _, status) = find(dir.path(), &["-name", "*.rs", "-type", "f"]);
+        assert_eq!(status, 0);
+        assert_eq!(out, "./src/main.rs\n./src/nested/lib.rs\n./target/out.rs\n");
+
+        let (out, _, _) = find(dir.path(), &["src", "-maxdepth", "1", "-print"]);
+        assert_eq!(out, "src\nsrc/main.rs\nsrc/nested\n");
+
+        let (out, _, _) = find(
+            dir.path(),
+            &[
+                ".", "-name", "target", "-prune", "-o", "-type", "f", "-print",
+            ],
+        );
+        assert_eq!(out, "./README.md\n./src/main.rs\n./src/nested/lib.rs\n");
+
+        let (out, _, _) = find(dir.path(), &["-size", "+1k", "-o", "-empty", "-type", "f"]);
+        assert_eq!(out, "./src/nested/lib.rs\n./target/out.rs\n");
+
+        let (out, _, _) = find(
+            dir.path(),
+            &["src", "-depth", "-mindepth", "1", "-printf", "%d %y %f\\n"],
+        );
+        assert_eq!(out, "1 f main.rs\n2 f lib.rs\n1 d nested\n");
+
+        let (out, _, _) = find(dir.path(), &["-path", "./src/*", "-print0", "-quit"]);
+        assert_eq!(out, "./src/main.rs\0");
+
+        let (out, err, status) = find(dir.path(), &["missing", "README.md"]);
+        assert_eq!(status, 1);
+        assert_eq!(out, "README.md\n");
+        assert_eq!(err, "find: 'missing': No such file or directory\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_links_by_policy_and_runs_commands() {
+        let dir = tree();
+        std::os::unix::fs::symlink(dir.path().join("src"), dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("src/loop")).unwrap();
+
+        let (out, _, _) = find(dir.path(), &["link", "-type", "d"]);
+        assert_eq!(out, "");
+        let (out, _, _) = find(dir.path(), &["-H", "link", "-maxdepth", "1", "-type", "d"]);
+        assert_eq!(out, "link\nlink/nested\n");
+        let (_, err, status) = find(dir.path(), &["-L", "src", "-name", "x"]);
+        assert_eq!(status, 1);
+        assert!(err.contains("File system loop detected"), "{err}");
+
+        let (out, _, status) = find(
+            dir.path(),
+            &["src", "-name", "*.rs", "-exec", "echo", "file:", "{}", "+"],
+        );
+        assert_eq!(status, 0);
+        assert_eq!(out, "file: src/main.rs src/nested/lib.rs\n");
+        let (out, _, _) = find(
+            dir.path(),
+            &["src", "-name", "*.rs", "-execdir", "echo", "[{}]", ";"],
+        );
+        assert_eq!(out, "[./main.rs]\n[./lib.rs]\n");
+        let (out, _, _) = find(
+            dir.path(),
+            &["src", "-type", "f", "-exec", "false", ";", "-o", "-print"],
+        );
+        assert_eq!(
+            out,
+            "src\nsrc/loop\nsrc/main.rs\nsrc/nested\nsrc/nested/lib.rs\n"
+        );
+
+        let (_, _, status) = find(dir.path(), &["target", "-delete"]);
+        assert_eq!(status, 0);
+        assert!(!dir.path().join("target").exists());
+    }
+}
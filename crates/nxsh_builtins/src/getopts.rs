@@ -1,70 +1,167 @@
-//! `getopts` builtin  EPOSIX argument parsing helper.
-//! Syntax: getopts OPTSTRING NAME [ARGS...]
-//! For each option parsed, sets variable NAME to the option character and prints index.
-//! If option requires an argument (indicated by ':' in OPTSTRING), the argument is stored in
-//! variable `OPTARG` in the shell context.
-//! On end of options, returns with status 1.
-
-use anyhow::{anyhow, Result};
+//! `getopts` builtin - POSIX option parsing for scripts
+//!
+//! Syntax: getopts OPTSTRING NAME [ARG...]
+//!
+//! Each call parses the next option from ARGs (or the positional parameters)
+//! and stores the option letter in NAME. `OPTIND` holds the index of the next
+//! argument to process and `OPTARG` the option's argument. A letter followed
+//! by `:` in OPTSTRING takes an argument. A leading `:` selects silent error
+//! reporting: NAME is set to `?` (unknown option) or `:` (missing argument)
+//! and OPTARG to the offending letter, and nothing is printed.
+//!
+//! Returns 0 while options remain and 1 at the end of options, leaving
+//! OPTIND pointing at the first operand.
+
 use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+
+/// Position inside a clustered option word (`-abc`), kept between calls.
+/// Stored as `OPTIND:offset` and ignored once the script changes OPTIND.
+const CLUSTER_KEY: &str = "__NXSH_GETOPTS_POS";
+
+/// The `getopts` builtin command implementation
+pub struct GetoptsCommand;
+
+impl Builtin for GetoptsCommand {
+    fn name(&self) -> &'static str {
+        "getopts"
+    }
 
-pub fn getopts_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    if args.len() < 2 {
-        return Err(anyhow!("getopts: missing arguments"));
+    fn synopsis(&self) -> &'static str {
+        "Parse option arguments"
     }
-    let optstr = &args[0];
-    let name = &args[1];
-    let argv: Vec<String> = if args.len() > 2 { args[2..].to_vec() } else { vec![] };
-
-    // Pointer index variable maintains current index in argv within context ($OPTIND style)
-    let mut optind: usize = ctx
-        .get_var("OPTIND")
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(1);
-
-    if optind == 0 { optind = 1; }
-    if optind > argv.len() { return Ok(()); }
-
-    let current = &argv[optind - 1];
-    if !current.starts_with('-') || current == "-" { return Ok(()); }
-    if current == "--" { return Ok(()); }
-
-    let opt_char = current.chars().nth(1).unwrap();
-    if !optstr.contains(opt_char) {
-        ctx.set_var(name, "?".to_string());
-        ctx.set_var("OPTARG", opt_char.to_string());
-        return Err(anyhow!("illegal option -- {}", opt_char));
+
+    fn description(&self) -> &'static str {
+        "Parse the positional parameters (or the given ARGs) as options, one per call, \
+         setting NAME, OPTARG and OPTIND."
     }
 
-    ctx.set_var(name, opt_char.to_string());
-    // option argument if required
-    if optstr.contains(&format!("{opt_char}:")) {
-        if current.len() > 2 {
-            ctx.set_var("OPTARG", current[2..].to_string());
-        } else if optind < argv.len() {
-            ctx.set_var("OPTARG", argv[optind].clone());
-            optind += 1;
+    fn usage(&self) -> &'static str {
+        "getopts optstring name [arg ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Parse option arguments. Use in a while loop: while getopts 'ab:' opt; do ...; done"
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // getopts updates NAME, OPTARG and OPTIND
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        if args.len() < 2 {
+            return Ok(ExecutionResult::failure(2)
+                .with_error(format!("getopts: usage: {}\n", self.usage()).into_bytes()));
+        }
+        let (silent, optstring) = match args[0].strip_prefix(':') {
+            Some(rest) => (true, rest),
+            None => (false, args[0].as_str()),
+        };
+        let name = &args[1];
+        let argv = if args.len() > 2 {
+            args[2..].to_vec()
         } else {
-            return Err(anyhow!("option requires an argument -- {}", opt_char));
+            ctx.positional_args()
+        };
+
+        let mut optind = ctx
+            .get_var("OPTIND")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&i| i > 0)
+            .unwrap_or(1);
+        let mut offset = ctx
+            .get_var(CLUSTER_KEY)
+            .and_then(|v| {
+                let (ind, off) = v.split_once(':')?;
+                if ind.parse::<usize>().ok()? != optind {
+                    return None;
+                }
+                off.parse::<usize>().ok()
+            })
+            .unwrap_or(1);
+
+        let word = match argv.get(optind - 1) {
+            Some(w) if w.starts_with('-') && w.len() > 1 => w.clone(),
+            _ => return Ok(end_of_options(ctx, name, optind)),
+        };
+        if word == "--" {
+            return Ok(end_of_options(ctx, name, optind + 1));
         }
-    } else {
-        ctx.set_var("OPTARG", String::new());
+
+        let letters: Vec<char> = word.chars().collect();
+        let letter = letters[offset.min(letters.len() - 1)];
+        offset += 1;
+        let at_word_end = offset >= letters.len();
+        let mut stderr = String::new();
+
+        if letter == ':' || !optstring.contains(letter) {
+            ctx.set_var(name, "?");
+            if silent {
+                ctx.set_var("OPTARG", letter.to_string());
+            } else {
+                ctx.unset_var("OPTARG");
+                stderr.push_str(&format!("getopts: illegal option -- {letter}\n"));
+            }
+        } else if takes_argument(optstring, letter) {
+            // Argument is the rest of this word, or the next word
+            if !at_word_end {
+                ctx.set_var("OPTARG", letters[offset..].iter().collect::<String>());
+                ctx.set_var(name, letter.to_string());
+            } else if let Some(value) = argv.get(optind) {
+                ctx.set_var("OPTARG", value.clone());
+                ctx.set_var(name, letter.to_string());
+                optind += 1;
+            } else if silent {
+                ctx.set_var(name, ":");
+                ctx.set_var("OPTARG", letter.to_string());
+            } else {
+                ctx.set_var(name, "?");
+                ctx.unset_var("OPTARG");
+                stderr.push_str(&format!(
+                    "getopts: option requires an argument -- {letter}\n"
+                ));
+            }
+            // The option word is consumed whole
+            offset = letters.len();
+        } else {
+            ctx.set_var(name, letter.to_string());
+            ctx.unset_var("OPTARG");
+        }
+        // Clustered options (`-abc`) stay on this word until its last letter
+        if offset >= letters.len() {
+            optind += 1;
+            offset = 1;
+        }
+        ctx.set_var("OPTIND", optind.to_string());
+        ctx.set_var(CLUSTER_KEY, format!("{optind}:{offset}"));
+
+        Ok(ExecutionResult::success(0).with_error(stderr.into_bytes()))
     }
-    optind += 1;
-    ctx.set_var("OPTIND", optind.to_string());
-    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl GetoptsCommand {
+    /// Create a new getopts command instance
+    pub fn new() -> Self {
+        GetoptsCommand
+    }
+}
 
-    #[test]
-    fn simple_getopts() {
-        let ctx = ShellContext::new();
-        let args = vec!["ab:".into(), "opt".into(), "-a".into(), "-b".into(), "val".into()];
-        getopts_cli(&args, &ctx).unwrap();
-        assert_eq!(ctx.get_var("opt").unwrap(), "a");
+impl Default for GetoptsCommand {
+    fn default() -> Self {
+        Self::new()
     }
-} 
+}
 
+fn takes_argument(optstring: &str, letter: char) -> bool {
+    optstring
+        .find(letter)
+        .is_some_and(|pos| optstring[pos + letter.len_utf8()..].starts_with(':'))
+}
+
+/// No more options: NAME becomes `?` and OPTIND points at the first operand
+fn end_of_options(ctx: &ShellContext, name: &str, optind: usize) -> ExecutionResult {
+    ctx.set_var(name, "?");
+    ctx.set_var("OPTIND", optind.to_string());
+    ctx.unset_var(CLUSTER_KEY);
+    ExecutionResult::failure(1)
+}
@@ -0,0 +1,1186 @@
+//! `grep` builtin - print lines that match patterns
+//!
+//! Syntax:
+//!   grep [OPTION...] PATTERN [FILE...]
+//!   grep [OPTION...] -e PATTERN... [-f FILE]... [FILE...]
+//!
+//! Patterns are basic regular expressions by default, extended ones with
+//! `-E`, literal strings with `-F`, and the regex crate's Perl-style syntax
+//! with `-P` (no lookaround or backreferences). Basic and extended patterns
+//! are translated to that syntax, so `\<`, `\>`, `\{n,m\}` and bracket
+//! expressions like `[[:digit:]]` mean what they do in GNU grep.
+//!
+//! With no FILE, or a FILE of `-`, standard input is searched; with `-r` a
+//! missing FILE means the current directory. A file holding a NUL byte is
+//! binary: a match is reported as `Binary file NAME matches` unless `-a` is
+//! given, and `-I` skips it.
+//!
+//! Files compressed with gzip, bzip2, xz or zstd are searched as their
+//! expanded contents, so `grep foo log.gz` works; `--decompress` expands
+//! standard input too and `--no-decompress` searches the raw bytes. `-z`
+//! is left for GNU grep's NUL-separated input.
+//!
+//! `--color` highlights matches, file names, line numbers and separators
+//! with the theme's `grep.match`, `grep.filename`, `grep.line_number` and
+//! `grep.separator` styles, falling back to GNU grep's colors. `GREP_COLORS`
+//! entries (`ms=`, `fn=`, `ln=`, `se=`) override both.
+//!
+//! The exit status is 0 when a line was selected, 1 when none was and 2 on
+//! an error, unless `-q` found a match first.
+
+use crate::common::decompress::{self, Decompress};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crossterm::style::ContentStyle;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use regex::bytes::{Regex, RegexBuilder};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+
+/// The `grep` builtin command implementation
+pub struct GrepCommand;
+
+impl Builtin for GrepCommand {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print lines that match patterns"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search files, directories with -r, or standard input for lines matching basic, \
+         extended (-E), fixed (-F) or Perl-style (-P) patterns, with optional context \
+         lines and colored matches."
+    }
+
+    fn usage(&self) -> &'static str {
+        "grep [-EFGP] [-ivwxnchHlLoqsrRaI] [-A NUM] [-B NUM] [-C NUM] [-m NUM] \
+         [--color[=WHEN]] [--include=GLOB] [--exclude=GLOB] [--exclude-dir=GLOB] \
+         [--[no-]decompress] {PATTERN | -e PATTERN... | -f FILE...} [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print lines that match patterns. Use 'grep -r PATTERN DIR' to search a tree."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run grep for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+pub fn grep_cli(args: &[String]) -> Result<(), anyhow::Error> {
+    let context = BuiltinContext::new();
+    match execute(args, &context)? {
+        2 => Err(anyhow::anyhow!("grep: search failed")),
+        _ => Ok(()),
+    }
+}
+
+/// What a grep run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+fn run(args: &[String], stdin: &mut dyn Read) -> Outcome {
+    let fail = |message: String| Outcome {
+        stdout: Vec::new(),
+        stderr: format!("grep: {message}\n").into_bytes(),
+        status: 2,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return fail(message),
+    };
+    let matcher = match Matcher::new(&options) {
+        Ok(matcher) => matcher,
+        Err(message) => return fail(message),
+    };
+    let colors = options.color.then(Colors::load);
+    let mut search = Search {
+        options: &options,
+        matcher: &matcher,
+        colors,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        selected: false,
+        failed: false,
+        done: false,
+        printed_group: false,
+    };
+    if options.files.is_empty() && options.recursive {
+        search.directory(Path::new("."), true);
+    } else if options.files.is_empty() {
+        search.stdin(stdin);
+    } else {
+        for file in &options.files {
+            if search.done {
+                break;
+            }
+            if file == "-" {
+                search.stdin(stdin);
+            } else {
+                search.path(Path::new(file), true);
+            }
+        }
+    }
+    let status = match (search.selected, search.failed) {
+        (true, _) if options.quiet => 0,
+        (_, true) => 2,
+        (true, false) => 0,
+        (false, false) => 1,
+    };
+    Outcome {
+        stdout: search.stdout,
+        stderr: search.stderr,
+        status,
+    }
+}
+
+/// How patterns are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Basic,
+    Extended,
+    Fixed,
+    Perl,
+}
+
+/// What to do with files that hold a NUL byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binary {
+    /// Report a match without printing lines
+    Report,
+    /// Search and print them like text
+    Text,
+    /// Skip them as if nothing matched
+    Skip,
+}
+
+#[derive(Debug)]
+struct Options {
+    syntax: Syntax,
+    patterns: Vec<String>,
+    ignore_case: bool,
+    invert: bool,
+    word: bool,
+    line: bool,
+    line_number: bool,
+    count: bool,
+    files_with_matches: bool,
+    files_without_match: bool,
+    only_matching: bool,
+    quiet: bool,
+    no_messages: bool,
+    recursive: bool,
+    dereference: bool,
+    with_filename: bool,
+    before: usize,
+    after: usize,
+    max_count: Option<u64>,
+    color: bool,
+    binary: Binary,
+    decompress: Decompress,
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+    exclude_dir: Vec<glob::Pattern>,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            syntax: Syntax::Basic,
+            patterns: Vec::new(),
+            ignore_case: false,
+            invert: false,
+            word: false,
+            line: false,
+            line_number: false,
+            count: false,
+            files_with_matches: false,
+            files_without_match: false,
+            only_matching: false,
+            quiet: false,
+            no_messages: false,
+            recursive: false,
+            dereference: false,
+            with_filename: false,
+            before: 0,
+            after: 0,
+            max_count: None,
+            color: false,
+            binary: Binary::Report,
+            decompress: Decompress::Files,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            exclude_dir: Vec::new(),
+            files: Vec::new(),
+        };
+        let mut filename = None;
+        let mut pattern_given = false;
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let mut value = || match inline.clone() {
+                    Some(value) => Ok(value),
+                    None => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '--{name}' requires an argument")),
+                };
+                match name {
+                    "regexp" => {
+                        options.add_patterns(&value()?);
+                        pattern_given = true;
+                    }
+                    "file" => {
+                        options.add_pattern_file(&value()?)?;
+                        pattern_given = true;
+                    }
+                    "after-context" => options.after = context_count(&value()?)?,
+                    "before-context" => options.before = context_count(&value()?)?,
+                    "context" => {
+                        let count = context_count(&value()?)?;
+                        options.after = count;
+                        options.before = count;
+                    }
+                    "max-count" => options.max_count = Some(max_count(&value()?)?),
+                    "include" => options.include.push(glob_pattern(&value()?)?),
+                    "exclude" => options.exclude.push(glob_pattern(&value()?)?),
+                    "exclude-dir" => options.exclude_dir.push(glob_pattern(&value()?)?),
+                    "color" | "colour" => {
+                        options.color = match inline.as_deref() {
+                            None | Some("auto") | Some("tty") | Some("if-tty") => {
+                                io::stdout().is_terminal()
+                                    && !std::env::var("TERM").is_ok_and(|t| t == "dumb")
+                            }
+                            Some("always") | Some("yes") | Some("force") => true,
+                            Some("never") | Some("no") | Some("none") => false,
+                            Some(other) => {
+                                return Err(format!("invalid argument '{other}' for '--color'"))
+                            }
+                        }
+                    }
+                    "binary-files" => {
+                        options.binary = match value()?.as_str() {
+                            "binary" => Binary::Report,
+                            "text" => Binary::Text,
+                            "without-match" => Binary::Skip,
+                            other => {
+                                return Err(format!(
+                                    "invalid argument '{other}' for '--binary-files'"
+                                ))
+                            }
+                        }
+                    }
+                    _ if inline.is_some() => return Err(format!("unrecognized option '--{name}'")),
+                    _ => match long_flag(name) {
+                        Some(flag) => options.set_flag(flag, &mut filename),
+                        None => return Err(format!("unrecognized option '--{name}'")),
+                    },
+                }
+                continue;
+            }
+            let Some(cluster) = arg.strip_prefix('-').filter(|c| !c.is_empty()) else {
+                operands.push(arg.clone());
+                continue;
+            };
+            let mut chars = cluster.char_indices();
+            while let Some((at, flag)) = chars.next() {
+                if flag.is_ascii_digit() {
+                    // `-NUM` is the same as `-C NUM`
+                    let digits: String = cluster[at..]
+                        .chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect();
+                    for _ in 1..digits.len() {
+                        chars.next();
+                    }
+                    let count = context_count(&digits)?;
+                    options.after = count;
+                    options.before = count;
+                    continue;
+                }
+                if "ABCefm".contains(flag) {
+                    let rest = &cluster[at + flag.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        iter.next()
+                            .cloned()
+                            .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?
+                    } else {
+                        rest.to_string()
+                    };
+                    match flag {
+                        'A' => options.after = context_count(&value)?,
+                        'B' => options.before = context_count(&value)?,
+                        'C' => {
+                            let count = context_count(&value)?;
+                            options.after = count;
+                            options.before = count;
+                        }
+                        'e' => {
+                            options.add_patterns(&value);
+                            pattern_given = true;
+                        }
+                        'f' => {
+                            options.add_pattern_file(&value)?;
+                            pattern_given = true;
+                        }
+                        _ => options.max_count = Some(max_count(&value)?),
+                    }
+                    break;
+                }
+                match short_flag(flag) {
+                    Some(flag) => options.set_flag(flag, &mut filename),
+                    None => return Err(format!("invalid option -- '{flag}'")),
+                }
+            }
+        }
+        let mut operands = operands.into_iter();
+        if !pattern_given {
+            let pattern = operands
+                .next()
+                .ok_or("usage: grep [OPTION...] PATTERNS [FILE...]")?;
+            options.add_patterns(&pattern);
+        }
+        options.files = operands.collect();
+        options.with_filename = filename.unwrap_or(options.recursive || options.files.len() > 1);
+        Ok(options)
+    }
+
+    /// Add the newline-separated patterns of one `-e` or operand
+    fn add_patterns(&mut self, patterns: &str) {
+        self.patterns
+            .extend(patterns.split('\n').map(str::to_string));
+    }
+
+    /// Add one pattern per line of FILE; an empty file adds none
+    fn add_pattern_file(&mut self, file: &str) -> Result<(), String> {
+        let text = if file == "-" {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("-: {}", io_message(&e)))?;
+            text
+        } else {
+            std::fs::read_to_string(file).map_err(|e| format!("{file}: {}", io_message(&e)))?
+        };
+        self.patterns.extend(text.lines().map(str::to_string));
+        Ok(())
+    }
+
+    fn set_flag(&mut self, flag: Flag, filename: &mut Option<bool>) {
+        match flag {
+            Flag::Syntax(syntax) => self.syntax = syntax,
+            Flag::IgnoreCase => self.ignore_case = true,
+            Flag::NoIgnoreCase => self.ignore_case = false,
+            Flag::Invert => self.invert = true,
+            Flag::Word => self.word = true,
+            Flag::Line => self.line = true,
+            Flag::LineNumber => self.line_number = true,
+            Flag::Count => self.count = true,
+            Flag::FilesWithMatches => self.files_with_matches = true,
+            Flag::FilesWithoutMatch => self.files_without_match = true,
+            Flag::OnlyMatching => self.only_matching = true,
+            Flag::Quiet => self.quiet = true,
+            Flag::NoMessages => self.no_messages = true,
+            Flag::Recursive => self.recursive = true,
+            Flag::Dereference => {
+                self.recursive = true;
+                self.dereference = true;
+            }
+            Flag::WithFilename => *filename = Some(true),
+            Flag::NoFilename => *filename = Some(false),
+            Flag::Text => self.binary = Binary::Text,
+            Flag::SkipBinary => self.binary = Binary::Skip,
+            Flag::Decompress(mode) => self.decompress = mode,
+        }
+    }
+}
+
+/// A flag that takes no value
+#[derive(Debug, Clone, Copy)]
+enum Flag {
+    Syntax(Syntax),
+    IgnoreCase,
+    NoIgnoreCase,
+    Invert,
+    Word,
+    Line,
+    LineNumber,
+    Count,
+    FilesWithMatches,
+    FilesWithoutMatch,
+    OnlyMatching,
+    Quiet,
+    NoMessages,
+    Recursive,
+    Dereference,
+    WithFilename,
+    NoFilename,
+    Text,
+    SkipBinary,
+    Decompress(Decompress),
+}
+
+fn short_flag(flag: char) -> Option<Flag> {
+    Some(match flag {
+        'G' => Flag::Syntax(Syntax::Basic),
+        'E' => Flag::Syntax(Syntax::Extended),
+        'F' => Flag::Syntax(Syntax::Fixed),
+        'P' => Flag::Syntax(Syntax::Perl),
+        'i' | 'y' => Flag::IgnoreCase,
+        'v' => Flag::Invert,
+        'w' => Flag::Word,
+        'x' => Flag::Line,
+        'n' => Flag::LineNumber,
+        'c' => Flag::Count,
+        'l' => Flag::FilesWithMatches,
+        'L' => Flag::FilesWithoutMatch,
+        'o' => Flag::OnlyMatching,
+        'q' => Flag::Quiet,
+        's' => Flag::NoMessages,
+        'r' => Flag::Recursive,
+        'R' => Flag::Dereference,
+        'H' => Flag::WithFilename,
+        'h' => Flag::NoFilename,
+        'a' => Flag::Text,
+        'I' => Flag::SkipBinary,
+        _ => return None,
+    })
+}
+
+fn long_flag(name: &str) -> Option<Flag> {
+    Some(match name {
+        "basic-regexp" => Flag::Syntax(Syntax::Basic),
+        "extended-regexp" => Flag::Syntax(Syntax::Extended),
+        "fixed-strings" => Flag::Syntax(Syntax::Fixed),
+        "perl-regexp" => Flag::Syntax(Syntax::Perl),
+        "ignore-case" => Flag::IgnoreCase,
+        "no-ignore-case" => Flag::NoIgnoreCase,
+        "invert-match" => Flag::Invert,
+        "word-regexp" => Flag::Word,
+        "line-regexp" => Flag::Line,
+        "line-number" => Flag::LineNumber,
+        "count" => Flag::Count,
+        "files-with-matches" => Flag::FilesWithMatches,
+        "files-without-match" => Flag::FilesWithoutMatch,
+        "only-matching" => Flag::OnlyMatching,
+        "quiet" | "silent" => Flag::Quiet,
+        "no-messages" => Flag::NoMessages,
+        "recursive" => Flag::Recursive,
+        "dereference-recursive" => Flag::Dereference,
+        "with-filename" => Flag::WithFilename,
+        "no-filename" => Flag::NoFilename,
+        "text" => Flag::Text,
+        "decompress" => Flag::Decompress(Decompress::Always),
+        "no-decompress" => Flag::Decompress(Decompress::Never),
+        _ => return None,
+    })
+}
+
+fn context_count(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{value}: invalid context length argument"))
+}
+
+fn max_count(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid max count '{value}'"))
+}
+
+fn glob_pattern(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|e| format!("{value}: {e}"))
+}
+
+/// The compiled patterns of one grep run
+struct Matcher {
+    /// `None` when every pattern came from an empty `-f` file
+    regex: Option<Regex>,
+    word: bool,
+}
+
+impl Matcher {
+    fn new(options: &Options) -> Result<Self, String> {
+        if options.patterns.is_empty() {
+            return Ok(Matcher {
+                regex: None,
+                word: false,
+            });
+        }
+        let alternatives = options
+            .patterns
+            .iter()
+            .map(|pattern| match options.syntax {
+                Syntax::Basic => translate(pattern, false),
+                Syntax::Extended => translate(pattern, true),
+                Syntax::Fixed => Ok(regex::escape(pattern)),
+                Syntax::Perl => Ok(pattern.clone()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut source = alternatives
+            .iter()
+            .map(|alternative| format!("(?:{alternative})"))
+            .collect::<Vec<_>>()
+            .join("|");
+        if options.line {
+            source = format!("^(?:{source})$");
+        }
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => message,
+                other => other.to_string(),
+            })?;
+        Ok(Matcher {
+            regex: Some(regex),
+            word: options.word && !options.line,
+        })
+    }
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.find(line, 0).is_some()
+    }
+
+    /// The first match in `line` at or after `start`, as a byte range
+    fn find(&self, line: &[u8], mut start: usize) -> Option<(usize, usize)> {
+        let regex = self.regex.as_ref()?;
+        while start <= line.len() {
+            let found = regex.find_at(line, start)?;
+            let (from, to) = (found.start(), found.end());
+            if !self.word || is_word_bounded(line, from, to) {
+                return Some((from, to));
+            }
+            start = from + 1;
+        }
+        None
+    }
+}
+
+/// Whether `line[from..to]` is neither preceded nor followed by a word
+/// character, which is what `-w` asks of a match
+fn is_word_bounded(line: &[u8], from: usize, to: usize) -> bool {
+    let word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80;
+    (from == 0 || !word(line[from - 1])) && (to == line.len() || !word(line[to]))
+}
+
+/// Translate a POSIX basic or extended regular expression to the regex
+/// crate's syntax; `sed` uses this for its addresses and `s` patterns too
+pub(crate) fn translate(pattern: &str, extended: bool) -> Result<String, String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::new();
+    // Whether the next character starts an expression, where `*` is literal
+    let mut at_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let starting = at_start;
+        at_start = false;
+        match c {
+            '\\' => {
+                let Some(&next) = chars.get(i + 1) else {
+                    return Err("trailing backslash (\\)".to_string());
+                };
+                i += 1;
+                match next {
+                    '(' | ')' | '{' | '}' | '|' | '+' | '?' if !extended => {
+                        if next == '{' {
+                            i = interval(&chars, i, &mut out, true)?;
+                            continue;
+                        }
+                        out.push(next);
+                        at_start = next == '(' || next == '|';
+                    }
+                    '<' | '>' => out.push_str("\\b"),
+                    'w' | 'W' | 's' | 'S' | 'b' | 'B' => {
+                        out.push('\\');
+                        out.push(next);
+                    }
+                    'n' => out.push_str("\\n"),
+                    't' => out.push_str("\\t"),
+                    '`' => out.push_str("\\A"),
+                    '\'' => out.push_str("\\z"),
+                    '1'..='9' => return Err("backreferences are not supported".to_string()),
+                    other => out.push_str(&regex::escape(&other.to_string())),
+                }
+            }
+            '[' => i = bracket(&chars, i, &mut out)?,
+            '*' if starting => out.push_str("\\*"),
+            '^' if extended || starting => {
+                out.push('^');
+                at_start = true;
+            }
+            '$' if extended || basic_end(&chars, i) => out.push('$'),
+            '(' | '|' if extended => {
+                out.push(c);
+                at_start = true;
+            }
+            ')' | '+' | '?' if extended => out.push(c),
+            '{' if extended && is_interval(&chars, i) => {
+                i = interval(&chars, i, &mut out, false)?;
+                continue;
+            }
+            '.' | '*' => out.push(c),
+            other => out.push_str(&regex::escape(&other.to_string())),
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+/// Whether the `$` at `i` of a basic expression ends it, or the group or
+/// alternative it is in
+fn basic_end(chars: &[char], i: usize) -> bool {
+    match chars.get(i + 1..i + 3) {
+        None => i + 1 == chars.len(),
+        Some(['\\', ')']) | Some(['\\', '|']) => true,
+        Some(_) => false,
+    }
+}
+
+/// Whether the extended `{` at `i` opens an interval like `{2}` or `{1,3}`
+/// rather than standing for itself
+fn is_interval(chars: &[char], i: usize) -> bool {
+    let body: String = chars[i + 1..].iter().take_while(|&&c| c != '}').collect();
+    i + 1 + body.len() < chars.len()
+        && !body.is_empty()
+        && body.chars().all(|c| c.is_ascii_digit() || c == ',')
+        && body.chars().filter(|&c| c == ',').count() <= 1
+        && body != ","
+}
+
+/// Copy the interval whose `{` is at `i`, ending with `}` or `\}` in a
+/// basic expression, and return the index past it
+fn interval(chars: &[char], i: usize, out: &mut String, basic: bool) -> Result<usize, String> {
+    let mut j = i + 1;
+    let mut body = String::new();
+    loop {
+        match chars.get(j) {
+            Some('\\') if basic && chars.get(j + 1) == Some(&'}') => {
+                j += 2;
+                break;
+            }
+            Some('}') if !basic => {
+                j += 1;
+                break;
+            }
+            Some(&c) if c.is_ascii_digit() || c == ',' => body.push(c),
+            _ => return Err("unmatched \\{".to_string()),
+        }
+        j += 1;
+    }
+    out.push('{');
+    out.push_str(&body);
+    out.push('}');
+    Ok(j)
+}
+
+/// Copy the bracket expression starting at `i`, escaping what the regex
+/// crate would read differently, and return the index past it
+fn bracket(chars: &[char], i: usize, out: &mut String) -> Result<usize, String> {
+    let mut j = i + 1;
+    out.push('[');
+    if chars.get(j) == Some(&'^') {
+        out.push('^');
+        j += 1;
+    }
+    if chars.get(j) == Some(&']') {
+        out.push_str("\\]");
+        j += 1;
+    }
+    while let Some(&c) = chars.get(j) {
+        match c {
+            ']' => {
+                out.push(']');
+                return Ok(j + 1);
+            }
+            '[' if matches!(chars.get(j + 1), Some(':') | Some('=') | Some('.')) => {
+                let kind = chars[j + 1];
+                let end = (j + 2..chars.len().saturating_sub(1))
+                    .find(|&k| chars[k] == kind && chars[k + 1] == ']')
+                    .ok_or("unmatched [, [^, [:, [., or [=")?;
+                let name: String = chars[j + 2..end].iter().collect();
+                match kind {
+                    ':' => out.push_str(&format!("[:{name}:]")),
+                    _ => out.push_str(&regex::escape(&name)),
+                }
+                j = end + 2;
+                continue;
+            }
+            '\\' | '[' | '&' | '~' => {
+                out.push('\\');
+                out.push(c);
+            }
+            other => out.push(other),
+        }
+        j += 1;
+    }
+    Err("unmatched [, [^, [:, [., or [=".to_string())
+}
+
+/// How each part of the output is colored
+struct Colors {
+    matched: Paint,
+    filename: Paint,
+    line_number: Paint,
+    separator: Paint,
+}
+
+/// A color from `GREP_COLORS` or the theme
+enum Paint {
+    Sgr(String),
+    Style(ContentStyle),
+}
+
+impl Paint {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Paint::Sgr(sgr) if sgr.is_empty() => text.to_string(),
+            Paint::Sgr(sgr) => format!("\x1b[{sgr}m\x1b[K{text}\x1b[m\x1b[K"),
+            Paint::Style(style) => style.apply(text).to_string(),
+        }
+    }
+}
+
+impl Colors {
+    /// GNU grep's colors, then the active theme's, then `GREP_COLORS`
+    fn load() -> Self {
+        let mut colors = Colors {
+            matched: Paint::Sgr("01;31".to_string()),
+            filename: Paint::Sgr("35".to_string()),
+            line_number: Paint::Sgr("32".to_string()),
+            separator: Paint::Sgr("36".to_string()),
+        };
+        let theme_name = nxsh_ui::UiConfig::default().theme_name;
+        if let Ok(theme) = nxsh_ui::get_theme(&theme_name) {
+            let style = |name: &str| {
+                theme
+                    .styles
+                    .get(name)
+                    .map(|style| Paint::Style(style.clone().into()))
+            };
+            if let Some(paint) = style("grep.match") {
+                colors.matched = paint;
+            }
+            if let Some(paint) = style("grep.filename") {
+                colors.filename = paint;
+            }
+            if let Some(paint) = style("grep.line_number") {
+                colors.line_number = paint;
+            }
+            if let Some(paint) = style("grep.separator") {
+                colors.separator = paint;
+            }
+        }
+        if let Ok(spec) = std::env::var("GREP_COLORS") {
+            for entry in spec.split(':') {
+                let Some((key, sgr)) = entry.split_once('=') else {
+                    continue;
+                };
+                let paint = Paint::Sgr(sgr.to_string());
+                match key {
+                    "ms" | "mt" => colors.matched = paint,
+                    "fn" => colors.filename = paint,
+                    "ln" => colors.line_number = paint,
+                    "se" => colors.separator = paint,
+                    _ => {}
+                }
+            }
+        }
+        colors
+    }
+}
+
+/// The state of one grep run across all its files
+struct Search<'a> {
+    options: &'a Options,
+    matcher: &'a Matcher,
+    colors: Option<Colors>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// Whether any line was selected
+    selected: bool,
+    /// Whether a file could not be read
+    failed: bool,
+    /// Set once `-q` has its answer
+    done: bool,
+    /// Whether a group of context lines was printed, so the next one is
+    /// preceded by `--`
+    printed_group: bool,
+}
+
+impl Search<'_> {
+    fn stdin(&mut self, stdin: &mut dyn Read) {
+        match decompress::read_to_end(stdin, self.options.decompress.applies(true)) {
+            Ok(data) => self.contents("(standard input)", &data),
+            Err(e) => self.error("(standard input)", &e),
+        }
+    }
+
+    /// Search a FILE operand, or with `-r` a file found while walking
+    fn path(&mut self, path: &Path, operand: bool) {
+        let name = path.to_string_lossy();
+        let metadata = if operand || self.options.dereference {
+            std::fs::metadata(path)
+        } else {
+            std::fs::symlink_metadata(path)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => return self.error(&name, &e),
+        };
+        if metadata.is_dir() {
+            if !self.options.recursive {
+                if !self.options.no_messages {
+                    self.stderr
+                        .extend(format!("grep: {name}: Is a directory\n").as_bytes());
+                }
+                return;
+            }
+            let excluded = path
+                .file_name()
+                .is_some_and(|base| matches_any(&self.options.exclude_dir, base));
+            if !excluded || operand {
+                self.directory(path, false);
+            }
+            return;
+        }
+        // `-r` leaves out symbolic links it was not given
+        if metadata.file_type().is_symlink() || !self.wanted(path) {
+            return;
+        }
+        let decode = self.options.decompress.applies(false);
+        match std::fs::File::open(path).and_then(|file| decompress::read_to_end(file, decode)) {
+            Ok(data) => self.contents(&name, &data),
+            Err(e) => self.error(&name, &e),
+        }
+    }
+
+    /// Search every entry of a directory in name order; the entries of the
+    /// implicit `.` are named without a `./` prefix
+    fn directory(&mut self, path: &Path, implicit: bool) {
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => return self.error(&path.to_string_lossy(), &e),
+        };
+        let mut names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+        names.sort();
+        for name in names {
+            if self.done {
+                return;
+            }
+            let child = if implicit {
+                Path::new(&name).to_path_buf()
+            } else {
+                path.join(&name)
+            };
+            self.path(&child, false);
+        }
+    }
+
+    /// Whether `--include` and `--exclude` let a file through
+    fn wanted(&self, path: &Path) -> bool {
+        let Some(base) = path.file_name() else {
+            return true;
+        };
+        if matches_any(&self.options.exclude, base) {
+            return false;
+        }
+        self.options.include.is_empty() || matches_any(&self.options.include, base)
+    }
+
+    fn error(&mut self, name: &str, e: &io::Error) {
+        self.failed = true;
+        if !self.options.no_messages {
+            self.stderr
+                .extend(format!("grep: {name}: {}\n", io_message(e)).as_bytes());
+        }
+    }
+
+    /// Search the contents of one file
+    fn contents(&mut self, name: &str, data: &[u8]) {
+        let options = self.options;
+        let binary = options.binary != Binary::Text && data.contains(&0);
+        if binary && options.binary == Binary::Skip {
+            return;
+        }
+        let summary = options.quiet
+            || options.count
+            || options.files_with_matches
+            || options.files_without_match;
+        let print_lines = !summary && !binary;
+        let mut lines: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+        if data.ends_with(b"\n") || data.is_empty() {
+            lines.pop();
+        }
+
+        let mut count = 0u64;
+        let mut last_printed: Option<usize> = None;
+        let mut after_left = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let limit_reached = options.max_count.is_some_and(|max| count >= max);
+            let selected = !limit_reached && self.matcher.is_match(line) != options.invert;
+            if !selected {
+                if limit_reached && after_left == 0 {
+                    break;
+                }
+                if print_lines && after_left > 0 && !options.only_matching {
+                    self.line(name, i, line, false);
+                    last_printed = Some(i);
+                    after_left -= 1;
+                }
+                continue;
+            }
+            count += 1;
+            self.selected = true;
+            if options.quiet {
+                self.done = true;
+                return;
+            }
+            if options.files_with_matches || options.files_without_match || (binary && !summary) {
+                break;
+            }
+            if !print_lines {
+                continue;
+            }
+            if options.only_matching {
+                self.only_matching(name, i, line);
+                continue;
+            }
+            let from = i
+                .saturating_sub(options.before)
+                .max(last_printed.map_or(0, |p| p + 1));
+            let context = options.before > 0 || options.after > 0;
+            let detached = match last_printed {
+                Some(p) => from > p + 1,
+                None => self.printed_group,
+            };
+            if context && detached {
+                let separator = self.paint(|c| &c.separator, "--");
+                self.stdout.extend(separator.as_bytes());
+                self.stdout.push(b'\n');
+            }
+            for (j, before) in lines.iter().enumerate().take(i).skip(from) {
+                self.line(name, j, before, false);
+            }
+            self.line(name, i, line, true);
+            self.printed_group = true;
+            last_printed = Some(i);
+            after_left = options.after;
+        }
+
+        let name_painted = self.paint(|c| &c.filename, name);
+        if options.count {
+            if options.with_filename {
+                let separator = self.paint(|c| &c.separator, ":");
+                self.stdout
+                    .extend(format!("{name_painted}{separator}").as_bytes());
+            }
+            self.stdout.extend(format!("{count}\n").as_bytes());
+        }
+        if (options.files_with_matches && count > 0) || (options.files_without_match && count == 0)
+        {
+            self.stdout.extend(format!("{name_painted}\n").as_bytes());
+        } else if binary && count > 0 && !summary {
+            self.stdout
+                .extend(format!("Binary file {name} matches\n").as_bytes());
+        }
+    }
+
+    /// Print a selected (`:`) or context (`-`) line with its prefix
+    fn line(&mut self, name: &str, index: usize, line: &[u8], selected: bool) {
+        self.prefix(name, index, if selected { ":" } else { "-" });
+        match &self.colors {
+            Some(colors) if selected && !self.options.invert => {
+                let mut start = 0;
+                let mut at = 0;
+                while let Some((from, to)) = self.matcher.find(line, at) {
+                    if to == from {
+                        at = from + 1;
+                        continue;
+                    }
+                    let before = String::from_utf8_lossy(&line[start..from]);
+                    let matched = colors
+                        .matched
+                        .apply(&String::from_utf8_lossy(&line[from..to]));
+                    self.stdout.extend(before.as_bytes());
+                    self.stdout.extend(matched.as_bytes());
+                    start = to;
+                    at = to;
+                }
+                self.stdout.extend(&line[start..]);
+            }
+            _ => self.stdout.extend(line),
+        }
+        self.stdout.push(b'\n');
+    }
+
+    /// Print each non-empty match of a selected line on its own line
+    fn only_matching(&mut self, name: &str, index: usize, line: &[u8]) {
+        if self.options.invert {
+            return;
+        }
+        let mut at = 0;
+        while let Some((from, to)) = self.matcher.find(line, at) {
+            at = if to == from { from + 1 } else { to };
+            if to == from {
+                continue;
+            }
+            self.prefix(name, index, ":");
+            let matched = self.paint(|c| &c.matched, &String::from_utf8_lossy(&line[from..to]));
+            self.stdout.extend(matched.as_bytes());
+            self.stdout.push(b'\n');
+        }
+    }
+
+    fn prefix(&mut self, name: &str, index: usize, separator: &str) {
+        let separator = self.paint(|c| &c.separator, separator);
+        if self.options.with_filename {
+            let name = self.paint(|c| &c.filename, name);
+            self.stdout.extend(format!("{name}{separator}").as_bytes());
+        }
+        if self.options.line_number {
+            let number = self.paint(|c| &c.line_number, &(index + 1).to_string());
+            self.stdout
+                .extend(format!("{number}{separator}").as_bytes());
+        }
+    }
+
+    fn paint(&self, part: impl Fn(&Colors) -> &Paint, text: &str) -> String {
+        match &self.colors {
+            Some(colors) => part(colors).apply(text),
+            None => text.to_string(),
+        }
+    }
+}
+
+fn matches_any(patterns: &[glob::Pattern], name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    patterns.iter().any(|pattern| pattern.matches(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(args: &[&str], input: &str) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, &mut input.as_bytes());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn translates_basic_and_extended_syntax() {
+        assert_eq!(translate(r"a\(b\)*c", false).unwrap(), "a(b)*c");
+        assert_eq!(translate("a(b)+", false).unwrap(), r"a\(b\)\+");
+        assert_eq!(translate(r"x\{2,3\}", false).unwrap(), "x{2,3}");
+        assert_eq!(translate("*a", false).unwrap(), r"\*a");
+        assert_eq!(translate("a^b$", false).unwrap(), r"a\^b$");
+        assert_eq!(translate(r"\<ab\>", true).unwrap(), r"\bab\b");
+        assert_eq!(translate("a{x", true).unwrap(), r"a\{x");
+        assert_eq!(translate("[[:digit:]&]", true).unwrap(), r"[[:digit:]\&]");
+        assert_eq!(translate(r"[]\a]", true).unwrap(), r"[\]\\a]");
+        assert!(translate(r"\(a\)\1", false).is_err());
+    }
+
+    #[test]
+    fn selects_lines_with_flags() {
+        let input = "apple\nBanana\ncherry\napple pie\n";
+        assert_eq!(grep(&["apple"], input), ("apple\napple pie\n".into(), 0));
+        assert_eq!(grep(&["-in", "banana"], input), ("2:Banana\n".into(), 0));
+        assert_eq!(grep(&["-vc", "apple"], input), ("2\n".into(), 0));
+        assert_eq!(grep(&["-x", "apple"], input), ("apple\n".into(), 0));
+        assert_eq!(grep(&["-w", "pie"], input), ("apple pie\n".into(), 0));
+        assert_eq!(grep(&["-w", "pi"], input).1, 1);
+        assert_eq!(grep(&["-oE", "an+"], input), ("an\nan\n".into(), 0));
+        assert_eq!(grep(&["-F", "a.p"], "a.p\nabp\n"), ("a.p\n".into(), 0));
+        assert_eq!(grep(&["-P", r"\d+"], "x1\ny\n"), ("x1\n".into(), 0));
+        assert_eq!(
+            grep(&["-e", "cherry", "-e", "Ban"], input).0,
+            "Banana\ncherry\n"
+        );
+        assert_eq!(grep(&["-m1", "apple"], input).0, "apple\n");
+    }
+
+    #[test]
+    fn prints_context_groups() {
+        let input = "1\n2\nx\n4\n5\n6\n7\nx\n9\n";
+        assert_eq!(
+            grep(&["-n", "-C1", "x"], input).0,
+            "2-2\n3:x\n4-4\n--\n7-7\n8:x\n9-9\n"
+        );
+        assert_eq!(grep(&["-A", "3", "x"], input).0, "x\n4\n5\n6\n--\nx\n9\n");
+    }
+
+    #[test]
+    fn reports_binary_input() {
+        assert_eq!(
+            grep(&["ab"], "ab\0cd\n"),
+            ("Binary file (standard input) matches\n".into(), 0)
+        );
+        assert_eq!(grep(&["-a", "cd"], "ab\0cd\n").0, "ab\0cd\n");
+        assert_eq!(grep(&["-I", "ab"], "ab\0cd\n").1, 1);
+    }
+
+    #[cfg(feature = "compression-gzip")]
+    #[test]
+    fn searches_compressed_input() {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        let packed = encoder.finish().unwrap();
+        let search = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            let outcome = run(&args, &mut packed.as_slice());
+            (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+        };
+        assert_eq!(search(&["--decompress", "tw"]), ("two\n".into(), 0));
+        assert_eq!(search(&["tw"]).1, 1);
+    }
+
+    #[test]
+    fn colors_matches() {
+        std::env::set_var("GREP_COLORS", "ms=01;32:se=");
+        let (out, _) = grep(&["--color=always", "-n", "b"], "abc\n");
+        std::env::remove_var("GREP_COLORS");
+        assert_eq!(
+            out,
+            "\x1b[32m\x1b[K1\x1b[m\x1b[K:a\x1b[01;32m\x1b[Kb\x1b[m\x1b[Kc\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        assert_eq!(grep(&[], "").1, 2);
+        assert_eq!(grep(&["-E", "a("], "").1, 2);
+        assert_eq!(grep(&["-A", "x", "a"], "").1, 2);
+    }
+}
@@ -0,0 +1,834 @@
+//! `gzip`, `gunzip` and `zcat` builtins - compress or expand files
+//!
+//! Syntax:
+//!   gzip [-cdfklnNqrtv1-9] [-S SUFFIX] [FILE...]
+//!   gunzip [OPTION...] [FILE...]
+//!   zcat [OPTION...] [FILE...]
+//!
+//! `gunzip` is `gzip -d` and `zcat` is `gzip -dc`. Each FILE is replaced by
+//! FILE.gz, or when decompressing FILE.gz (or .tgz, .taz) by FILE, and the
+//! new file takes the original's mode and times. With `-c`, `-t` or `-l`, or
+//! for a FILE of `-` or no FILE at all, nothing is replaced: data goes from
+//! the files or standard input to standard output, is only checked, or only
+//! summarised. `zcat -f` copies input that is not gzip data unchanged.
+//!
+//! Compressed files record the original name and modification time unless
+//! `-n` is given, and `gunzip -N` restores them from there.
+//!
+//! The exit status is 0 on success, 1 after an error and 2 when there were
+//! only warnings, such as a FILE skipped because it already has the suffix.
+//!
+//! The commands need the `compression-gzip` feature.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Read, Write};
+use std::path::Path;
+#[cfg(feature = "compression-gzip")]
+use {
+    crate::common::io_message,
+    flate2::{bufread::MultiGzDecoder, Compression, GzBuilder},
+    std::fs,
+    std::io::BufReader,
+    std::path::PathBuf,
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How the command was invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Program {
+    Gzip,
+    Gunzip,
+    Zcat,
+}
+
+impl Program {
+    fn name(self) -> &'static str {
+        match self {
+            Program::Gzip => "gzip",
+            Program::Gunzip => "gunzip",
+            Program::Zcat => "zcat",
+        }
+    }
+}
+
+/// The `gzip` builtin command implementation
+pub struct GzipCommand;
+
+/// The `gunzip` builtin command implementation
+pub struct GunzipCommand;
+
+/// The `zcat` builtin command implementation
+pub struct ZcatCommand;
+
+fn execute_as(
+    program: Program,
+    ctx: &mut ShellContext,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let cwd = ctx.cwd.clone();
+    let outcome = run(program, args, &cwd, &mut ctx.stdin);
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+impl Builtin for GzipCommand {
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compress or expand files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Replace each file with a gzip-compressed copy named FILE.gz, or with -d the \
+         reverse, keeping modes and times; -c writes to standard output instead, -t \
+         checks and -l summarises compressed files."
+    }
+
+    fn usage(&self) -> &'static str {
+        "gzip [-cdfklnNqrtv1-9] [-S SUFFIX] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compress or expand files. Use 'gzip -k FILE' to keep the original and \
+         'gzip -d FILE.gz' to expand."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Program::Gzip, ctx, args)
+    }
+}
+
+impl Builtin for GunzipCommand {
+    fn name(&self) -> &'static str {
+        "gunzip"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Expand gzip files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Replace each FILE.gz with its expanded contents named FILE, keeping modes and \
+         times; the same as gzip -d."
+    }
+
+    fn usage(&self) -> &'static str {
+        "gunzip [-cfklnNqrtv] [-S SUFFIX] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Expand gzip files. Use 'gunzip -c FILE.gz' to write to standard output."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Program::Gunzip, ctx, args)
+    }
+}
+
+impl Builtin for ZcatCommand {
+    fn name(&self) -> &'static str {
+        "zcat"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print gzip files expanded"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write the expanded contents of gzip files, or of standard input, to standard \
+         output; the same as gzip -dc."
+    }
+
+    fn usage(&self) -> &'static str {
+        "zcat [-fqv] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print gzip files expanded. Use 'zcat FILE.gz | grep PATTERN' to search one."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Program::Zcat, ctx, args)
+    }
+}
+
+fn execute_legacy(program: Program, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(program, args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Run gzip for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Program::Gzip, args)
+}
+
+/// Run gunzip for the legacy dispatcher
+pub fn gunzip_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Program::Gunzip, args)
+}
+
+/// Run zcat for the legacy dispatcher
+pub fn zcat_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Program::Zcat, args)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[cfg(not(feature = "compression-gzip"))]
+fn run(program: Program, _args: &[String], _cwd: &Path, _stdin: &mut dyn Read) -> Outcome {
+    Outcome {
+        stdout: Vec::new(),
+        stderr: format!(
+            "{}: gzip support is not built in; enable the compression-gzip feature\n",
+            program.name()
+        )
+        .into_bytes(),
+        status: 1,
+    }
+}
+
+#[cfg(feature = "compression-gzip")]
+fn run(program: Program, args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut gzip = match Options::parse(program, args) {
+        Ok(options) => Gzip {
+            options,
+            cwd: cwd.to_path_buf(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+            listed: None,
+        },
+        Err(message) => {
+            return Outcome {
+                stdout: Vec::new(),
+                stderr: format!("{}: {message}\n", program.name()).into_bytes(),
+                status: 1,
+            }
+        }
+    };
+    let files = match gzip.options.files.is_empty() {
+        true => vec!["-".to_string()],
+        false => gzip.options.files.clone(),
+    };
+    for file in &files {
+        gzip.operand(file, stdin, false);
+    }
+    if let Some(totals) = gzip.listed.filter(|totals| totals.count > 1) {
+        gzip.list_line(totals.compressed, totals.uncompressed, "(totals)");
+    }
+    Outcome {
+        stdout: gzip.stdout,
+        stderr: gzip.stderr,
+        status: gzip.status,
+    }
+}
+
+/// Parsed command line
+#[cfg(feature = "compression-gzip")]
+struct Options {
+    program: Program,
+    decompress: bool,
+    stdout: bool,
+    force: bool,
+    keep: bool,
+    list: bool,
+    test: bool,
+    /// `-N` or `-n`; by default names are saved but not restored
+    name: Option<bool>,
+    quiet: bool,
+    recursive: bool,
+    verbose: bool,
+    level: u32,
+    suffix: String,
+    files: Vec<String>,
+}
+
+#[cfg(feature = "compression-gzip")]
+impl Options {
+    fn parse(program: Program, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            program,
+            decompress: program != Program::Gzip,
+            stdout: program == Program::Zcat,
+            force: false,
+            keep: false,
+            list: false,
+            test: false,
+            name: None,
+            quiet: false,
+            recursive: false,
+            verbose: false,
+            level: 6,
+            suffix: ".gz".to_string(),
+            files: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                options.files.extend(args.by_ref().cloned());
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                if name == "suffix" {
+                    let value = match inline {
+                        Some(value) => value.to_string(),
+                        None => args
+                            .next()
+                            .cloned()
+                            .ok_or("option '--suffix' requires an argument")?,
+                    };
+                    options.set_suffix(value)?;
+                    continue;
+                }
+                if inline.is_some() {
+                    return Err(format!("option '--{name}' doesn't allow an argument"));
+                }
+                let letter = match name {
+                    "stdout" | "to-stdout" => 'c',
+                    "decompress" | "uncompress" => 'd',
+                    "force" => 'f',
+                    "keep" => 'k',
+                    "list" => 'l',
+                    "no-name" => 'n',
+                    "name" => 'N',
+                    "quiet" | "silent" => 'q',
+                    "recursive" => 'r',
+                    "test" => 't',
+                    "verbose" => 'v',
+                    "fast" => '1',
+                    "best" => '9',
+                    _ => return Err(format!("unrecognized option '--{name}'")),
+                };
+                options.flag(letter)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                let letters: Vec<char> = arg[1..].chars().collect();
+                for (index, &letter) in letters.iter().enumerate() {
+                    if letter == 'S' {
+                        let rest: String = letters[index + 1..].iter().collect();
+                        let value = match rest.is_empty() {
+                            true => args
+                                .next()
+                                .cloned()
+                                .ok_or("option requires an argument -- 'S'")?,
+                            false => rest,
+                        };
+                        options.set_suffix(value)?;
+                        break;
+                    }
+                    options.flag(letter)?;
+                }
+            } else {
+                options.files.push(arg.clone());
+            }
+        }
+        Ok(options)
+    }
+
+    fn set_suffix(&mut self, suffix: String) -> Result<(), String> {
+        if suffix.is_empty() || suffix.contains('/') {
+            return Err(format!("invalid suffix '{suffix}'"));
+        }
+        self.suffix = suffix;
+        Ok(())
+    }
+
+    fn flag(&mut self, letter: char) -> Result<(), String> {
+        match letter {
+            'c' => self.stdout = true,
+            'd' => self.decompress = true,
+            'f' => self.force = true,
+            'k' => self.keep = true,
+            'l' => self.list = true,
+            'n' => self.name = Some(false),
+            'N' => self.name = Some(true),
+            'q' => self.quiet = true,
+            'r' => self.recursive = true,
+            't' => self.test = true,
+            'v' => self.verbose = true,
+            '1'..='9' => self.level = letter.to_digit(10).unwrap_or(6),
+            _ => return Err(format!("invalid option -- '{letter}'")),
+        }
+        Ok(())
+    }
+
+    /// Whether data goes to standard output rather than replacing files
+    fn streams(&self) -> bool {
+        self.stdout || self.test || self.list
+    }
+}
+
+/// Running sums for `-l`
+#[cfg(feature = "compression-gzip")]
+#[derive(Clone, Copy)]
+struct Totals {
+    compressed: u64,
+    uncompressed: u64,
+    count: usize,
+}
+
+/// State of one run
+#[cfg(feature = "compression-gzip")]
+struct Gzip {
+    options: Options,
+    cwd: PathBuf,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+    /// Set once `-l` printed its heading
+    listed: Option<Totals>,
+}
+
+#[cfg(feature = "compression-gzip")]
+impl Gzip {
+    fn message(&mut self, text: String) {
+        self.stderr
+            .extend_from_slice(format!("{}: {text}\n", self.options.program.name()).as_bytes());
+    }
+
+    fn error(&mut self, text: String) {
+        self.message(text);
+        self.status = 1;
+    }
+
+    fn warn(&mut self, text: String) {
+        if !self.options.quiet {
+            self.message(text);
+        }
+        if self.status == 0 {
+            self.status = 2;
+        }
+    }
+
+    /// Handle one FILE operand, or a file met while recursing
+    fn operand(&mut self, name: &str, stdin: &mut dyn Read, recursing: bool) {
+        if name == "-" {
+            let mut data = Vec::new();
+            match stdin.read_to_end(&mut data) {
+                Ok(_) => self.stream("stdin", &data, None),
+                Err(e) => self.error(format!("stdin: {}", io_message(&e))),
+            }
+            return;
+        }
+        let path = self.cwd.join(name);
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => return self.error(format!("{name}: {}", io_message(&e))),
+        };
+        if metadata.is_dir() {
+            if !self.options.recursive {
+                return self.warn(format!("{name} is a directory -- ignored"));
+            }
+            let mut children: Vec<_> = match fs::read_dir(&path) {
+                Ok(entries) => entries.flatten().map(|e| e.file_name()).collect(),
+                Err(e) => return self.error(format!("{name}: {}", io_message(&e))),
+            };
+            children.sort();
+            for child in children {
+                let child = format!("{}/{}", name.trim_end_matches('/'), child.to_string_lossy());
+                self.operand(&child, stdin, true);
+            }
+            return;
+        }
+        if !metadata.is_file() {
+            return self.warn(format!(
+                "{name} is not a directory or a regular file - ignored"
+            ));
+        }
+
+        if self.options.list {
+            return self.list(name, &path, &metadata);
+        }
+        if self.options.streams() {
+            match fs::read(&path) {
+                Ok(data) => self.stream(name, &data, Some(&metadata)),
+                Err(e) => self.error(format!("{name}: {}", io_message(&e))),
+            }
+        } else if self.options.decompress {
+            self.expand_file(name, &path, &metadata, recursing);
+        } else {
+            self.compress_file(name, &path, &metadata, recursing);
+        }
+    }
+
+    /// A gzip encoder writing to SINK, recording the name and time of the
+    /// file NAME with METADATA unless `-n` was given
+    fn encoder<W: Write>(
+        &self,
+        sink: W,
+        name: &str,
+        metadata: Option<&fs::Metadata>,
+    ) -> flate2::write::GzEncoder<W> {
+        let mut builder = GzBuilder::new();
+        if let (Some(metadata), true) = (metadata, self.options.name != Some(false)) {
+            let base = Path::new(name).file_name().unwrap_or_default();
+            builder = builder.filename(base.to_string_lossy().as_bytes());
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |age| age.as_secs() as u32);
+            builder = builder.mtime(mtime);
+        }
+        builder.write(sink, Compression::new(self.options.level))
+    }
+
+    /// Compress, expand or test DATA read from NAME, writing to standard
+    /// output
+    fn stream(&mut self, name: &str, data: &[u8], metadata: Option<&fs::Metadata>) {
+        if !self.options.decompress {
+            let mut out = Vec::new();
+            let mut encoder = self.encoder(&mut out, name, metadata);
+            let result = encoder.write_all(data).and_then(|_| encoder.finish());
+            drop(result.map_err(|e| self.error(format!("{name}: {}", io_message(&e)))));
+            self.stdout.extend_from_slice(&out);
+            return;
+        }
+        if !data.starts_with(&[0x1f, 0x8b]) {
+            if self.options.force && self.options.stdout && !self.options.test {
+                self.stdout.extend_from_slice(data);
+            } else {
+                self.error(format!("{name}: not in gzip format"));
+            }
+            return;
+        }
+        let mut out = Vec::new();
+        match MultiGzDecoder::new(data).read_to_end(&mut out) {
+            Ok(_) if self.options.test => {
+                if self.options.verbose {
+                    self.message(format!("{name}:\t OK"));
+                }
+            }
+            Ok(_) => self.stdout.extend_from_slice(&out),
+            Err(e) => self.error(format!("{name}: {}", io_message(&e))),
+        }
+    }
+
+    fn compress_file(&mut self, name: &str, path: &Path, metadata: &fs::Metadata, recursing: bool) {
+        let suffix = self.options.suffix.clone();
+        if name.ends_with(&suffix) {
+            if !recursing || self.options.verbose {
+                self.warn(format!("{name} already has {suffix} suffix -- unchanged"));
+            }
+            return;
+        }
+        let target = PathBuf::from(format!("{}{suffix}", path.display()));
+        let target_name = format!("{name}{suffix}");
+        if !self.make_way(&target, &target_name) {
+            return;
+        }
+        let result = fs::File::open(path).and_then(|input| {
+            let output = io::BufWriter::new(fs::File::create(&target)?);
+            let mut encoder = self.encoder(output, name, Some(metadata));
+            io::copy(&mut BufReader::new(input), &mut encoder)?;
+            encoder.finish()?.flush()
+        });
+        self.finish(name, path, metadata, &target, &target_name, result, None);
+    }
+
+    fn expand_file(&mut self, name: &str, path: &Path, metadata: &fs::Metadata, recursing: bool) {
+        let suffix = self.options.suffix.clone();
+        let stripped = [suffix.as_str(), ".gz", "-gz", ".z", "-z", "_z"]
+            .iter()
+            .find_map(|s| name.strip_suffix(s))
+            .filter(|base| !base.is_empty() && !base.ends_with('/'))
+            .map(str::to_string)
+            .or_else(|| {
+                [".tgz", ".taz"].iter().find_map(|s| {
+                    name.strip_suffix(s)
+                        .filter(|base| !base.is_empty())
+                        .map(|base| format!("{base}.tar"))
+                })
+            });
+        let Some(mut target_name) = stripped else {
+            if !recursing {
+                self.warn(format!("{name}: unknown suffix -- ignored"));
+            }
+            return;
+        };
+
+        let input = match fs::File::open(path) {
+            Ok(input) => input,
+            Err(e) => return self.error(format!("{name}: {}", io_message(&e))),
+        };
+        let mut decoder = MultiGzDecoder::new(BufReader::new(input));
+        // Reading the first block parses the header, which -N takes the name
+        // and time from
+        let mut first = vec![0; 64 * 1024];
+        let len = match decoder.read(&mut first) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                return self.error(format!("{name}: not in gzip format"))
+            }
+            Err(e) => return self.error(format!("{name}: {}", io_message(&e))),
+        };
+        first.truncate(len);
+        let mut mtime = None;
+        if self.options.name == Some(true) {
+            if let Some(header) = decoder.header() {
+                let original = header
+                    .filename()
+                    .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .and_then(|n| n.rsplit('/').next().map(str::to_string))
+                    .filter(|n| !n.is_empty() && n != "." && n != "..");
+                if let Some(original) = original {
+                    target_name = match target_name.rfind('/') {
+                        Some(at) => format!("{}{original}", &target_name[..=at]),
+                        None => original,
+                    };
+                }
+                mtime = (header.mtime() != 0)
+                    .then(|| UNIX_EPOCH + Duration::from_secs(u64::from(header.mtime())));
+            }
+        }
+        let target = self.cwd.join(&target_name);
+        if !self.make_way(&target, &target_name) {
+            return;
+        }
+        let result = fs::File::create(&target).and_then(|output| {
+            let mut output = io::BufWriter::new(output);
+            output.write_all(&first)?;
+            io::copy(&mut decoder, &mut output)?;
+            output.flush()
+        });
+        self.finish(name, path, metadata, &target, &target_name, result, mtime);
+    }
+
+    /// Whether TARGET can be written, removing it with `-f`
+    fn make_way(&mut self, target: &Path, target_name: &str) -> bool {
+        if fs::symlink_metadata(target).is_err() {
+            return true;
+        }
+        if !self.options.force {
+            self.warn(format!("{target_name} already exists; not overwritten"));
+            return false;
+        }
+        match fs::remove_file(target) {
+            Ok(()) => true,
+            Err(e) => {
+                self.error(format!("{target_name}: {}", io_message(&e)));
+                false
+            }
+        }
+    }
+
+    /// Give TARGET the mode and times of the input file, remove that unless
+    /// `-k` was given, and report with `-v`; or clean up after a failure
+    #[allow(clippy::too_many_arguments)]
+    fn finish(
+        &mut self,
+        name: &str,
+        path: &Path,
+        metadata: &fs::Metadata,
+        target: &Path,
+        target_name: &str,
+        result: io::Result<()>,
+        mtime: Option<SystemTime>,
+    ) {
+        if let Err(e) = result {
+            let _ = fs::remove_file(target);
+            let message = match e.kind() {
+                io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+                    "invalid compressed data--format violated".to_string()
+                }
+                _ => io_message(&e),
+            };
+            return self.error(format!("{name}: {message}"));
+        }
+        let _ = fs::set_permissions(target, metadata.permissions());
+        let atime = filetime::FileTime::from_last_access_time(metadata);
+        let mtime = match mtime {
+            Some(time) => filetime::FileTime::from_system_time(time),
+            None => filetime::FileTime::from_last_modification_time(metadata),
+        };
+        let _ = filetime::set_file_times(target, atime, mtime);
+
+        if !self.options.keep {
+            if let Err(e) = fs::remove_file(path) {
+                self.error(format!("{name}: {}", io_message(&e)));
+            }
+        }
+        if self.options.verbose {
+            let (plain, packed) = match self.options.decompress {
+                true => (fs::metadata(target).map_or(0, |m| m.len()), metadata.len()),
+                false => (metadata.len(), fs::metadata(target).map_or(0, |m| m.len())),
+            };
+            let action = match self.options.keep {
+                true => "created",
+                false => "replaced with",
+            };
+            self.message(format!(
+                "{name}:\t{:5.1}% -- {action} {target_name}",
+                ratio(packed, plain)
+            ));
+        }
+    }
+
+    /// Summarise the gzip file NAME for `-l`
+    fn list(&mut self, name: &str, path: &Path, metadata: &fs::Metadata) {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => return self.error(format!("{name}: {}", io_message(&e))),
+        };
+        if !data.starts_with(&[0x1f, 0x8b]) || data.len() < 18 {
+            return self.error(format!("{name}: not in gzip format"));
+        }
+        // The trailer holds the expanded size modulo 2^32
+        let mut size = [0; 4];
+        size.copy_from_slice(&data[data.len() - 4..]);
+        let uncompressed = u64::from(u32::from_le_bytes(size));
+        let base = name
+            .strip_suffix(self.options.suffix.as_str())
+            .unwrap_or(name)
+            .to_string();
+        let totals = self.listed.get_or_insert(Totals {
+            compressed: 0,
+            uncompressed: 0,
+            count: 0,
+        });
+        let first = totals.count == 0;
+        totals.compressed += metadata.len();
+        totals.uncompressed += uncompressed;
+        totals.count += 1;
+        if first {
+            self.stdout.extend_from_slice(
+                b"         compressed        uncompressed  ratio uncompressed_name\n",
+            );
+        }
+        self.list_line(metadata.len(), uncompressed, &base);
+    }
+
+    fn list_line(&mut self, compressed: u64, uncompressed: u64, name: &str) {
+        let line = format!(
+            "{compressed:>19} {uncompressed:>19} {:5.1}% {name}\n",
+            ratio(compressed, uncompressed)
+        );
+        self.stdout.extend_from_slice(line.as_bytes());
+    }
+}
+
+/// The percentage by which PLAIN bytes shrank to PACKED ones
+#[cfg(feature = "compression-gzip")]
+fn ratio(packed: u64, plain: u64) -> f64 {
+    match plain {
+        0 => 0.0,
+        _ => 100.0 * (plain as f64 - packed as f64) / plain as f64,
+    }
+}
+
+#[cfg(all(test, feature = "compression-gzip"))]
+mod tests {
+    use super::*;
+
+    fn gzip(program: Program, cwd: &Path, list: &[&str], stdin: &[u8]) -> (Vec<u8>, String, i32) {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        let outcome = run(program, &args, cwd, &mut &stdin[..]);
+        (
+            outcome.stdout,
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    #[test]
+    fn replaces_files_and_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path();
+        fs::write(cwd.join("notes.txt"), "hello hello hello\n").unwrap();
+        filetime::set_file_mtime(
+            cwd.join("notes.txt"),
+            filetime::FileTime::from_unix_time(1_000_000, 0),
+        )
+        .unwrap();
+
+        let (_, err, status) = gzip(Program::Gzip, cwd, &["notes.txt"], b"");
+        assert_eq!((err.as_str(), status), ("", 0));
+        assert!(!cwd.join("notes.txt").exists());
+        let packed = cwd.join("notes.txt.gz");
+        assert_eq!(fs::read(&packed).unwrap()[..2], [0x1f, 0x8b]);
+
+        let (_, err, status) = gzip(Program::Gzip, cwd, &["notes.txt.gz"], b"");
+        assert_eq!(status, 2);
+        assert!(err.contains("already has .gz suffix"));
+
+        let (out, _, _) = gzip(Program::Gzip, cwd, &["-l", "notes.txt.gz"], b"");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().nth(1).unwrap().contains(" 18 "), "{out}");
+        assert!(out.ends_with(" notes.txt\n"));
+
+        let (_, err, status) = gzip(Program::Gunzip, cwd, &["notes.txt.gz"], b"");
+        assert_eq!((err.as_str(), status), ("", 0));
+        let metadata = fs::metadata(cwd.join("notes.txt")).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&metadata).unix_seconds(),
+            1_000_000
+        );
+        assert_eq!(
+            fs::read_to_string(cwd.join("notes.txt")).unwrap(),
+            "hello hello hello\n"
+        );
+    }
+
+    #[test]
+    fn streams_through_standard_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let (packed, _, status) = gzip(Program::Gzip, dir.path(), &["-9"], b"data\n");
+        assert_eq!(status, 0);
+        let (plain, _, status) = gzip(Program::Zcat, dir.path(), &[], &packed);
+        assert_eq!((plain.as_slice(), status), (&b"data\n"[..], 0));
+
+        let (_, err, status) = gzip(Program::Zcat, dir.path(), &[], b"plain\n");
+        assert_eq!(status, 1);
+        assert!(err.contains("stdin: not in gzip format"));
+        let (plain, _, status) = gzip(Program::Zcat, dir.path(), &["-f"], b"plain\n");
+        assert_eq!((plain.as_slice(), status), (&b"plain\n"[..], 0));
+
+        let (_, err, status) = gzip(Program::Gzip, dir.path(), &["-tv"], &packed);
+        assert_eq!((err.as_str(), status), ("gzip: stdin:\t OK\n", 0));
+    }
+
+    #[test]
+    fn restores_the_recorded_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path();
+        fs::write(cwd.join("report.csv"), "a,b\n").unwrap();
+        let (packed, _, _) = gzip(Program::Gzip, cwd, &["-c", "report.csv"], b"");
+        fs::write(cwd.join("renamed.gz"), packed).unwrap();
+
+        let (_, err, status) = gzip(Program::Gunzip, cwd, &["-N", "-f", "renamed.gz"], b"");
+        assert_eq!((err.as_str(), status), ("", 0));
+        assert_eq!(fs::read_to_string(cwd.join("report.csv")).unwrap(), "a,b\n");
+        assert!(!cwd.join("renamed").exists());
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, err, status) = gzip(Program::Gzip, dir.path(), &["-x"], b"");
+        assert_eq!((err.as_str(), status), ("gzip: invalid option -- 'x'\n", 1));
+        let (_, err, status) = gzip(Program::Gunzip, dir.path(), &["missing.gz"], b"");
+        assert_eq!(status, 1);
+        assert!(err.contains("missing.gz: No such file or directory"));
+        fs::write(dir.path().join("plain"), "x").unwrap();
+        let (_, err, status) = gzip(Program::Gunzip, dir.path(), &["plain"], b"");
+        assert_eq!(
+            (err.as_str(), status),
+            ("gunzip: plain: unknown suffix -- ignored\n", 2)
+        );
+    }
+}
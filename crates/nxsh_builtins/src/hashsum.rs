@@ -0,0 +1,926 @@
+//! `md5sum`, `sha1sum`, `sha256sum`, `sha512sum` and `b2sum` builtins -
+//! compute and check message digests
+//!
+//! Syntax:
+//!   sha256sum [-btz] [--tag] [FILE...]
+//!   sha256sum -c [-w] [--quiet] [--status] [--strict] [--ignore-missing] [FILE...]
+//!   b2sum [-l BITS] [OPTION...] [FILE...]
+//!
+//! Each FILE, standard input when there are none or for `-`, is hashed and
+//! listed as `DIGEST  NAME`, with `*` before NAME for `-b` (binary mode),
+//! or as `ALGORITHM (NAME) = DIGEST` with `--tag`. A NAME holding a
+//! backslash or newline is escaped and its line starts with a backslash,
+//! unless `-z` ends lines with NUL instead. `b2sum -l` sets the BLAKE2b
+//! digest length, a multiple of 8 up to 512 bits.
+//!
+//! With `-c` the FILEs are lists in either form, and each file listed is
+//! hashed again and reported `OK` or `FAILED`; lines that are neither form
+//! are skipped, reported with `-w` and an error with `--strict`. Files are
+//! hashed in parallel when the `parallel` feature is enabled. All digests
+//! are computed in Rust.
+//!
+//! The exit status is 0 on success and 1 when a FILE cannot be read or a
+//! check fails.
+
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use blake2::digest::VariableOutput;
+use blake2::Blake2bVar;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The digest a command computes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    Blake2b,
+}
+
+impl Algorithm {
+    fn program(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5sum",
+            Algorithm::Sha1 => "sha1sum",
+            Algorithm::Sha256 => "sha256sum",
+            Algorithm::Sha512 => "sha512sum",
+            Algorithm::Blake2b => "b2sum",
+        }
+    }
+
+    /// The name used by `--tag` lines
+    fn tag(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+            Algorithm::Blake2b => "BLAKE2b",
+        }
+    }
+
+    /// The digest length in bytes
+    fn size(self) -> usize {
+        match self {
+            Algorithm::Md5 => 16,
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha512 | Algorithm::Blake2b => 64,
+        }
+    }
+}
+
+/// An algorithm with its digest length, which only `b2sum` can vary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hash {
+    algorithm: Algorithm,
+    size: usize,
+}
+
+impl Hash {
+    /// The `--tag` name, which for BLAKE2b also gives a shortened length
+    fn tag(self) -> String {
+        match self.algorithm {
+            Algorithm::Blake2b if self.size != 64 => format!("BLAKE2b-{}", self.size * 8),
+            algorithm => algorithm.tag().to_string(),
+        }
+    }
+
+    fn digest(self, source: &Source) -> io::Result<Vec<u8>> {
+        let mut hasher = Hasher::new(self);
+        match source {
+            Source::Data(data) => hasher.update(data),
+            Source::File(path) => {
+                let mut file = File::open(path)?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    match file.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(len) => hasher.update(&buffer[..len]),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(hasher.finish())
+    }
+}
+
+/// A hash in progress
+enum Hasher {
+    Md5(md5::Context),
+    Sha1(sha1::Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake2b(Blake2bVar),
+}
+
+impl Hasher {
+    fn new(hash: Hash) -> Self {
+        match hash.algorithm {
+            Algorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            Algorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            // Sizes are checked to be 1 to 64 bytes when options are parsed
+            Algorithm::Blake2b => Hasher::Blake2b(
+                Blake2bVar::new(hash.size).expect("BLAKE2b digest size is between 1 and 64"),
+            ),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(context) => context.consume(data),
+            Hasher::Sha1(hasher) => Digest::update(hasher, data),
+            Hasher::Sha256(hasher) => Digest::update(hasher, data),
+            Hasher::Sha512(hasher) => Digest::update(hasher, data),
+            Hasher::Blake2b(hasher) => blake2::digest::Update::update(hasher, data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(context) => context.compute().0.to_vec(),
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+            Hasher::Blake2b(hasher) => {
+                let mut digest = vec![0; hasher.output_size()];
+                hasher
+                    .finalize_variable(&mut digest)
+                    .expect("buffer matches the digest size");
+                digest
+            }
+        }
+    }
+}
+
+/// Something to hash
+enum Source<'a> {
+    Data(&'a [u8]),
+    File(PathBuf),
+}
+
+/// The digests of SOURCES, computed in parallel with the `parallel` feature
+fn digest_all(hash: Hash, sources: &[Source]) -> Vec<io::Result<Vec<u8>>> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        sources
+            .par_iter()
+            .map(|source| hash.digest(source))
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        sources.iter().map(|source| hash.digest(source)).collect()
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// The `md5sum` builtin command implementation
+pub struct Md5sumCommand;
+
+/// The `sha1sum` builtin command implementation
+pub struct Sha1sumCommand;
+
+/// The `sha256sum` builtin command implementation
+pub struct Sha256sumCommand;
+
+/// The `sha512sum` builtin command implementation
+pub struct Sha512sumCommand;
+
+/// The `b2sum` builtin command implementation
+pub struct B2sumCommand;
+
+fn execute_as(
+    algorithm: Algorithm,
+    ctx: &mut ShellContext,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let cwd = ctx.cwd.clone();
+    let outcome = run(algorithm, args, &cwd, &mut ctx.stdin);
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+impl Builtin for Md5sumCommand {
+    fn name(&self) -> &'static str {
+        "md5sum"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compute and check MD5 digests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the MD5 digest of each FILE, or with -c check the digests listed in \
+         each FILE."
+    }
+
+    fn usage(&self) -> &'static str {
+        "md5sum [-bctwz] [--tag] [--quiet] [--status] [--strict] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compute and check MD5 digests. Use 'md5sum -c MD5SUMS' to verify downloads."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Algorithm::Md5, ctx, args)
+    }
+}
+
+impl Builtin for Sha1sumCommand {
+    fn name(&self) -> &'static str {
+        "sha1sum"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compute and check SHA-1 digests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the SHA-1 digest of each FILE, or with -c check the digests listed in \
+         each FILE."
+    }
+
+    fn usage(&self) -> &'static str {
+        "sha1sum [-bctwz] [--tag] [--quiet] [--status] [--strict] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compute and check SHA-1 digests. Use 'sha1sum -c SHA1SUMS' to verify downloads."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Algorithm::Sha1, ctx, args)
+    }
+}
+
+impl Builtin for Sha256sumCommand {
+    fn name(&self) -> &'static str {
+        "sha256sum"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compute and check SHA-256 digests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the SHA-256 digest of each FILE, or with -c check the digests listed in \
+         each FILE."
+    }
+
+    fn usage(&self) -> &'static str {
+        "sha256sum [-bctwz] [--tag] [--quiet] [--status] [--strict] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compute and check SHA-256 digests. Use 'sha256sum -c SHA256SUMS' to verify \
+         downloads."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Algorithm::Sha256, ctx, args)
+    }
+}
+
+impl Builtin for Sha512sumCommand {
+    fn name(&self) -> &'static str {
+        "sha512sum"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compute and check SHA-512 digests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the SHA-512 digest of each FILE, or with -c check the digests listed in \
+         each FILE."
+    }
+
+    fn usage(&self) -> &'static str {
+        "sha512sum [-bctwz] [--tag] [--quiet] [--status] [--strict] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compute and check SHA-512 digests. Use 'sha512sum -c SHA512SUMS' to verify \
+         downloads."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Algorithm::Sha512, ctx, args)
+    }
+}
+
+impl Builtin for B2sumCommand {
+    fn name(&self) -> &'static str {
+        "b2sum"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Compute and check BLAKE2b digests"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the BLAKE2b digest of each FILE, 512 bits or -l BITS long, or with -c \
+         check the digests listed in each FILE."
+    }
+
+    fn usage(&self) -> &'static str {
+        "b2sum [-bctwz] [-l BITS] [--tag] [--quiet] [--status] [--strict] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Compute and check BLAKE2b digests. Use 'b2sum -l 256 FILE' for a 256-bit digest."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_as(Algorithm::Blake2b, ctx, args)
+    }
+}
+
+fn execute_legacy(algorithm: Algorithm, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(algorithm, args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Run md5sum for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn md5sum_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Algorithm::Md5, args)
+}
+
+/// Run sha1sum for the legacy dispatcher
+pub fn sha1sum_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Algorithm::Sha1, args)
+}
+
+/// Run sha256sum for the legacy dispatcher
+pub fn sha256sum_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Algorithm::Sha256, args)
+}
+
+/// Run sha512sum for the legacy dispatcher
+pub fn sha512sum_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Algorithm::Sha512, args)
+}
+
+/// Run b2sum for the legacy dispatcher
+pub fn b2sum_execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    execute_legacy(Algorithm::Blake2b, args)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    hash: Hash,
+    binary: bool,
+    check: bool,
+    tag: bool,
+    zero: bool,
+    quiet: bool,
+    status: bool,
+    warn: bool,
+    strict: bool,
+    ignore_missing: bool,
+    /// Whether `-l` fixed the length, which `-c` otherwise reads from digests
+    fixed_length: bool,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(algorithm: Algorithm, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            hash: Hash {
+                algorithm,
+                size: algorithm.size(),
+            },
+            binary: false,
+            check: false,
+            tag: false,
+            zero: false,
+            quiet: false,
+            status: false,
+            warn: false,
+            strict: false,
+            ignore_missing: false,
+            fixed_length: false,
+            files: Vec::new(),
+        };
+        let blake = algorithm == Algorithm::Blake2b;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--binary" => options.binary = true,
+                "--text" => options.binary = false,
+                "--check" => options.check = true,
+                "--tag" => options.tag = true,
+                "--zero" => options.zero = true,
+                "--quiet" => options.quiet = true,
+                "--status" => options.status = true,
+                "--warn" => options.warn = true,
+                "--strict" => options.strict = true,
+                "--ignore-missing" => options.ignore_missing = true,
+                "--length" if blake => {
+                    let bits = iter
+                        .next()
+                        .ok_or_else(|| "option '--length' requires an argument".to_string())?;
+                    options.set_length(bits)?;
+                }
+                long if blake && long.starts_with("--length=") => {
+                    options.set_length(&long["--length=".len()..])?;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'b' => options.binary = true,
+                            't' => options.binary = false,
+                            'c' => options.check = true,
+                            'z' => options.zero = true,
+                            'w' => options.warn = true,
+                            'l' if blake => {
+                                let attached = &short[2 + at..];
+                                let bits = match attached {
+                                    "" => iter.next().map(String::as_str).ok_or_else(|| {
+                                        "option requires an argument -- 'l'".to_string()
+                                    })?,
+                                    _ => attached,
+                                };
+                                options.set_length(bits)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        if options.files.is_empty() {
+            options.files.push("-".to_string());
+        }
+        if options.check && (options.tag || options.binary) {
+            return Err(
+                "the --binary and --tag options are meaningless when verifying checksums"
+                    .to_string(),
+            );
+        }
+        if options.tag && options.binary {
+            return Err("--tag does not support --binary".to_string());
+        }
+        Ok(options)
+    }
+
+    fn set_length(&mut self, bits: &str) -> Result<(), String> {
+        match bits.parse::<usize>() {
+            Ok(bits) if (1..=512).contains(&bits) && bits.is_multiple_of(8) => {
+                self.hash.size = bits / 8;
+                self.fixed_length = true;
+                Ok(())
+            }
+            Ok(bits) if bits <= 512 => Err(format!("length is not a multiple of 8: '{bits}'")),
+            _ => Err(format!("invalid length: '{bits}'")),
+        }
+    }
+}
+
+/// NAME as written in a listing: backslashes and line breaks are escaped,
+/// in which case the line is marked with a leading backslash
+fn escape(name: &str) -> (&'static str, String) {
+    if !name.contains(['\\', '\n', '\r']) {
+        return ("", name.to_string());
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    ("\\", escaped)
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+/// One well-formed line of a checksum list
+#[derive(Debug, PartialEq)]
+struct Entry {
+    digest: Vec<u8>,
+    name: String,
+    hash: Hash,
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).ok())
+        .collect()
+}
+
+/// The entry LINE of a checksum list describes, if it is well formed
+fn parse_entry(options: &Options, line: &str) -> Option<Entry> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let mut hash = options.hash;
+    let (hex, name) = match line.rsplit_once(") = ") {
+        // ALGORITHM (NAME) = DIGEST
+        Some((head, hex)) if head.contains(" (") => {
+            let (tag, name) = head.split_once(" (")?;
+            if tag != hash.algorithm.tag() {
+                let bits = tag.strip_prefix("BLAKE2b-")?.parse::<usize>().ok()?;
+                let valid = bits.is_multiple_of(8) && bits <= 512;
+                if hash.algorithm != Algorithm::Blake2b || !valid {
+                    return None;
+                }
+                if options.fixed_length && bits / 8 != hash.size {
+                    return None;
+                }
+                hash.size = bits / 8;
+            } else if hash.algorithm == Algorithm::Blake2b && !options.fixed_length {
+                hash.size = 64;
+            }
+            (hex, name)
+        }
+        // DIGEST  NAME or DIGEST *NAME
+        _ => {
+            let end = line.find(|c: char| !c.is_ascii_hexdigit())?;
+            let (hex, rest) = line.split_at(end);
+            let name = rest
+                .strip_prefix(" ")
+                .and_then(|rest| rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*')))?;
+            if hash.algorithm == Algorithm::Blake2b && !options.fixed_length {
+                hash.size = hex.len() / 2;
+            }
+            (hex, name)
+        }
+    };
+    let digest = decode_hex(hex)?;
+    if digest.len() != hash.size || hash.size == 0 || name.is_empty() {
+        return None;
+    }
+    let name = if escaped {
+        unescape(name)?
+    } else {
+        name.to_string()
+    };
+    Some(Entry { digest, name, hash })
+}
+
+fn run(algorithm: Algorithm, args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let program = algorithm.program();
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(algorithm, args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("{program}: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let mut inputs = Inputs::new(cwd, stdin);
+    if options.check {
+        for name in &options.files {
+            check(&options, name, &mut inputs, &mut outcome);
+        }
+        return outcome;
+    }
+
+    // Standard input is read up front; files are hashed as they are read
+    let stdin_data = if options.files.iter().any(|name| name == "-") {
+        match inputs.read("-") {
+            Ok(data) => data,
+            Err(e) => {
+                outcome.stderr = format!("{program}: -: {}\n", io_message(&e)).into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let sources: Vec<Source> = options
+        .files
+        .iter()
+        .enumerate()
+        .map(|(index, name)| match name.as_str() {
+            "-" if options.files[..index].iter().any(|name| name == "-") => Source::Data(&[]),
+            "-" => Source::Data(&stdin_data),
+            name => Source::File(inputs.path(name)),
+        })
+        .collect();
+    let end = if options.zero { '\0' } else { '\n' };
+    for (name, digest) in options.files.iter().zip(digest_all(options.hash, &sources)) {
+        let digest = match digest {
+            Ok(digest) => hex(&digest),
+            Err(e) => {
+                outcome
+                    .stderr
+                    .extend(format!("{program}: {name}: {}\n", io_message(&e)).as_bytes());
+                outcome.status = 1;
+                continue;
+            }
+        };
+        let (mark, shown) = if options.zero {
+            ("", name.clone())
+        } else {
+            escape(name)
+        };
+        let line = if options.tag {
+            format!("{mark}{} ({shown}) = {digest}{end}", options.hash.tag())
+        } else {
+            let mode = if options.binary { '*' } else { ' ' };
+            format!("{mark}{digest} {mode}{shown}{end}")
+        };
+        outcome.stdout.extend(line.as_bytes());
+    }
+    outcome
+}
+
+/// Verify the checksum list LIST
+fn check(options: &Options, list: &str, inputs: &mut Inputs, outcome: &mut Outcome) {
+    let program = options.hash.algorithm.program();
+    let data = match inputs.read(list) {
+        Ok(data) => data,
+        Err(e) => {
+            outcome
+                .stderr
+                .extend(format!("{program}: {list}: {}\n", io_message(&e)).as_bytes());
+            outcome.status = 1;
+            return;
+        }
+    };
+    let shown_list = if list == "-" { "standard input" } else { list };
+    let mut entries = Vec::new();
+    let mut malformed = 0;
+    for (number, line) in lines(&data).into_iter().enumerate() {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        match parse_entry(options, line) {
+            Some(entry) => entries.push(entry),
+            None => {
+                malformed += 1;
+                if options.warn {
+                    outcome.stderr.extend(
+                        format!(
+                            "{program}: {shown_list}: {}: improperly formatted {} checksum line\n",
+                            number + 1,
+                            options.hash.algorithm.tag()
+                        )
+                        .as_bytes(),
+                    );
+                }
+            }
+        }
+    }
+    if entries.is_empty() {
+        outcome.stderr.extend(
+            format!("{program}: {shown_list}: no properly formatted checksum lines found\n")
+                .as_bytes(),
+        );
+        outcome.status = 1;
+        return;
+    }
+
+    let sources: Vec<Source> = entries
+        .iter()
+        .map(|entry| match entry.name.as_str() {
+            "-" => Source::Data(&[]),
+            name => Source::File(inputs.path(name)),
+        })
+        .collect();
+    // Entries may differ in length under b2sum, so only uniform lists take
+    // the parallel path
+    let digests: Vec<io::Result<Vec<u8>>> =
+        if entries.iter().all(|entry| entry.hash == entries[0].hash) {
+            digest_all(entries[0].hash, &sources)
+        } else {
+            entries
+                .iter()
+                .zip(&sources)
+                .map(|(entry, source)| entry.hash.digest(source))
+                .collect()
+        };
+
+    let (mut failed, mut unreadable, mut verified) = (0, 0, 0);
+    for (entry, digest) in entries.iter().zip(digests) {
+        let (mark, shown) = escape(&entry.name);
+        let result = match digest {
+            Ok(digest) if digest == entry.digest => {
+                verified += 1;
+                if options.quiet {
+                    continue;
+                }
+                "OK"
+            }
+            Ok(_) => {
+                failed += 1;
+                "FAILED"
+            }
+            Err(e) if options.ignore_missing && e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                unreadable += 1;
+                if !options.status {
+                    outcome.stderr.extend(
+                        format!("{program}: {}: {}\n", entry.name, io_message(&e)).as_bytes(),
+                    );
+                }
+                "FAILED open or read"
+            }
+        };
+        if !options.status {
+            outcome
+                .stdout
+                .extend(format!("{mark}{shown}: {result}\n").as_bytes());
+        }
+    }
+
+    if !options.status {
+        let plural = |count: usize, one: &str, many: &str| {
+            if count == 1 {
+                format!("{count} {one}")
+            } else {
+                format!("{count} {many}")
+            }
+        };
+        if malformed > 0 {
+            let lines = plural(malformed, "line is", "lines are");
+            outcome
+                .stderr
+                .extend(format!("{program}: WARNING: {lines} improperly formatted\n").as_bytes());
+        }
+        if unreadable > 0 {
+            let files = plural(unreadable, "listed file", "listed files");
+            outcome
+                .stderr
+                .extend(format!("{program}: WARNING: {files} could not be read\n").as_bytes());
+        }
+        if failed > 0 {
+            let sums = plural(failed, "computed checksum", "computed checksums");
+            outcome
+                .stderr
+                .extend(format!("{program}: WARNING: {sums} did NOT match\n").as_bytes());
+        }
+    }
+    if options.ignore_missing && verified == 0 && failed == 0 && unreadable == 0 {
+        outcome
+            .stderr
+            .extend(format!("{program}: {shown_list}: no file was verified\n").as_bytes());
+        outcome.status = 1;
+    }
+    if failed > 0 || unreadable > 0 || (options.strict && malformed > 0) {
+        outcome.status = 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sum(algorithm: Algorithm, args: &[&str], dir: &Path, input: &str) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(algorithm, &args, dir, &mut input.as_bytes());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
+
+    #[test]
+    fn computes_known_digests() {
+        let dir = Path::new(".");
+        assert_eq!(
+            sum(Algorithm::Md5, &[], dir, "abc").0,
+            "900150983cd24fb0d6963f7d28e17f72  -\n"
+        );
+        assert_eq!(
+            sum(Algorithm::Sha1, &["-b"], dir, "abc").0,
+            "a9993e364706816aba3e25717850c26c9cd0d89d *-\n"
+        );
+        assert_eq!(
+            sum(Algorithm::Sha256, &["--tag"], dir, "abc").0,
+            "SHA256 (-) = ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n"
+        );
+        assert!(sum(Algorithm::Sha512, &[], dir, "abc")
+            .0
+            .starts_with("ddaf35a193617aba"));
+        assert_eq!(
+            sum(Algorithm::Blake2b, &["-l", "256"], dir, "abc").0,
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319  -\n"
+        );
+    }
+
+    #[test]
+    fn checks_listed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "abc").unwrap();
+        std::fs::write(dir.path().join("b"), "xyz").unwrap();
+        let (listing, status) = sum(Algorithm::Sha256, &["a", "b"], dir.path(), "");
+        assert_eq!(status, 0);
+        std::fs::write(dir.path().join("SUMS"), &listing).unwrap();
+        assert_eq!(
+            sum(Algorithm::Sha256, &["-c", "SUMS"], dir.path(), ""),
+            ("a: OK\nb: OK\n".into(), 0)
+        );
+
+        std::fs::write(dir.path().join("b"), "changed").unwrap();
+        std::fs::remove_file(dir.path().join("a")).unwrap();
+        assert_eq!(
+            sum(
+                Algorithm::Sha256,
+                &["-c", "--quiet", "SUMS"],
+                dir.path(),
+                ""
+            ),
+            ("a: FAILED open or read\nb: FAILED\n".into(), 1)
+        );
+        assert_eq!(
+            sum(Algorithm::Sha256, &["-c", "--status"], dir.path(), &listing),
+            (String::new(), 1)
+        );
+
+        let tagged = sum(
+            Algorithm::Blake2b,
+            &["--tag", "-l", "128", "b"],
+            dir.path(),
+            "",
+        )
+        .0;
+        assert!(tagged.starts_with("BLAKE2b-128 (b) = "));
+        assert_eq!(
+            sum(Algorithm::Blake2b, &["-c", "-"], dir.path(), &tagged),
+            ("b: OK\n".into(), 0)
+        );
+        assert_eq!(
+            sum(
+                Algorithm::Md5,
+                &["-c", "--strict", "-"],
+                dir.path(),
+                "junk\n"
+            )
+            .1,
+            1
+        );
+    }
+
+    #[test]
+    fn escapes_awkward_names() {
+        assert_eq!(escape("a\\b\nc"), ("\\", "a\\\\b\\nc".to_string()));
+        assert_eq!(unescape("a\\\\b\\nc").as_deref(), Some("a\\b\nc"));
+    }
+}
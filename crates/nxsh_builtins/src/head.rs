@@ -1,3 +1,4 @@
+use crate::common::decompress::{self, Decompress};
 use crate::common::{BuiltinContext, BuiltinResult};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
@@ -9,6 +10,7 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
     let mut byte_count: Option<u64> = None;
     let mut quiet = false;
     let mut verbose = false;
+    let mut decompress = Decompress::Files;
     let mut files: Vec<String> = Vec::new();
 
     let mut i = 0;
@@ -44,6 +46,8 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
             }
             "-q" | "--quiet" | "--silent" => quiet = true,
             "-v" | "--verbose" => verbose = true,
+            "-z" | "--decompress" => decompress = Decompress::Always,
+            "--no-decompress" => decompress = Decompress::Never,
             "-h" | "--help" => {
                 print_help();
                 return Ok(0);
@@ -100,9 +104,9 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
         }
 
         let result = if filename == "-" {
-            read_from_stdin(line_count, byte_count)
+            read_from_stdin(line_count, byte_count, decompress)
         } else {
-            read_from_file(filename, line_count, byte_count)
+            read_from_file(filename, line_count, byte_count, decompress)
         };
 
         if let Err(e) = result {
@@ -118,17 +122,21 @@ fn read_from_file(
     filename: &str,
     line_count: i64,
     byte_count: Option<u64>,
+    decompress: Decompress,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if !Path::new(filename).exists() {
         return Err("No such file or directory".to_string().into());
     }
 
     let file = File::open(filename)?;
+    let reader: Box<dyn BufRead> = match decompress.applies(false) {
+        true => decompress::decompressed(file)?,
+        false => Box::new(BufReader::new(file)),
+    };
 
     if let Some(bytes) = byte_count {
-        read_bytes(Box::new(file), bytes)?;
+        read_bytes(reader, bytes)?;
     } else {
-        let reader = BufReader::new(file);
         read_lines(reader, line_count)?;
     }
 
@@ -138,13 +146,18 @@ fn read_from_file(
 fn read_from_stdin(
     line_count: i64,
     byte_count: Option<u64>,
+    decompress: Decompress,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let stdin = std::io::stdin();
+    let reader: Box<dyn BufRead> = match decompress.applies(true) {
+        true => decompress::decompressed(stdin.lock())?,
+        false => Box::new(stdin.lock()),
+    };
 
     if let Some(bytes) = byte_count {
-        read_bytes(Box::new(stdin.lock()), bytes)?;
+        read_bytes(reader, bytes)?;
     } else {
-        read_lines(stdin.lock(), line_count)?;
+        read_lines(reader, line_count)?;
     }
 
     Ok(())
@@ -197,6 +210,8 @@ fn print_help() {
     println!("  -n, --lines=NUM      print the first NUM lines instead of the first 10");
     println!("  -q, --quiet, --silent never print headers giving file names");
     println!("  -v, --verbose        always print headers giving file names");
+    println!("  -z, --decompress     expand compressed standard input too");
+    println!("      --no-decompress  never expand gzip, bzip2, xz or zstd input");
     println!("  -h, --help           display this help and exit");
     println!();
     println!("NUM may have a multiplier suffix:");
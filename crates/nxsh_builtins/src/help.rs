@@ -1,4 +1,6 @@
+use nxsh_ui::pager::{self, PagerOptions};
 use std::fmt;
+use std::io::{self, Write};
 
 pub struct HelpCommand;
 
@@ -14,14 +16,26 @@ pub fn execute(
     _context: &crate::common::BuiltinContext,
 ) -> Result<i32, crate::common::BuiltinError> {
     if args.is_empty() {
-        show_stylish_general_help();
+        // The full list is longer than most screens
+        let mut text = Vec::new();
+        write_general_help(&mut text)?;
+        let text = String::from_utf8_lossy(&text);
+        if pager::should_page(&text) {
+            let options = PagerOptions {
+                raw: true,
+                ..Default::default()
+            };
+            pager::page(&text, options, &mut io::stdout())?;
+        } else {
+            io::stdout().write_all(text.as_bytes())?;
+        }
     } else {
         show_stylish_command_help(&args[0]);
     }
     Ok(0)
 }
 
-fn show_stylish_general_help() {
+fn write_general_help(out: &mut impl Write) -> io::Result<()> {
     // Beautiful color scheme
     let cyan = "\x1b[38;2;0;245;255m"; // #00f5ff - Bright cyan
     let purple = "\x1b[38;2;153;69;255m"; // #9945ff - Electric purple
@@ -35,161 +49,396 @@ fn show_stylish_general_help() {
     let lavender = "\x1b[38;2;116;125;140m"; // #747d8c - Lavender
     let reset = "\x1b[0m";
 
-    println!();
-    println!("{cyan}╔══════════════════════════════════════════════════════════════════════════════╗{reset}");
-    println!("{cyan}║{purple}                    🚀 NEXUSSHELL COMPLETE COMMAND SUITE 🚀                   {cyan}║{reset}");
-    println!("{cyan}╚══════════════════════════════════════════════════════════════════════════════╝{reset}");
-    println!();
+    writeln!(out)?;
+    writeln!(out, "{cyan}╔══════════════════════════════════════════════════════════════════════════════╗{reset}")?;
+    writeln!(out, "{cyan}║{purple}                    🚀 NEXUSSHELL COMPLETE COMMAND SUITE 🚀                   {cyan}║{reset}")?;
+    writeln!(out, "{cyan}╚══════════════════════════════════════════════════════════════════════════════╝{reset}")?;
+    writeln!(out)?;
 
     // File Operations
-    println!("{purple}📂 FILE OPERATIONS & MANAGEMENT{reset}");
-    println!("  {yellow}ls{reset}        - 📋 List directory contents with style");
-    println!("  {yellow}pwd{reset}       - 📍 Show current working directory");
-    println!("  {yellow}cd{reset}        - 🔄 Change directory intelligently");
-    println!("  {yellow}touch{reset}     - ✨ Create/update file timestamps");
-    println!("  {yellow}mkdir{reset}     - 📁 Create directories recursively");
-    println!("  {yellow}cp{reset}        - 📄 Copy files and directories");
-    println!("  {yellow}mv{reset}        - 🔀 Move/rename files and folders");
-    println!("  {yellow}rm{reset}        - 🗑️  Remove files and directories");
-    println!("  {yellow}ln{reset}        - 🔗 Create symbolic/hard links");
-    println!("  {yellow}chmod{reset}     - 🔐 Change file permissions");
-    println!("  {yellow}chown{reset}     - 👤 Change file ownership");
-    println!("  {yellow}find{reset}      - 🔍 Advanced file search with patterns");
-    println!("  {yellow}locate{reset}    - ⚡ Fast file location");
-    println!("  {yellow}du{reset}        - 📊 Disk usage analysis");
-    println!("  {yellow}df{reset}        - 💿 Filesystem disk space info");
-    println!("  {yellow}stat{reset}      - 📋 Detailed file statistics");
-    println!();
+    writeln!(out, "{purple}📂 FILE OPERATIONS & MANAGEMENT{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}ls{reset}        - 📋 List directory contents with style"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}pwd{reset}       - 📍 Show current working directory"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}cd{reset}        - 🔄 Change directory intelligently"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}touch{reset}     - ✨ Create/update file timestamps"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}mkdir{reset}     - 📁 Create directories recursively"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}cp{reset}        - 📄 Copy files and directories"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}mv{reset}        - 🔀 Move/rename files and folders"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}rm{reset}        - 🗑️  Remove files and directories"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}ln{reset}        - 🔗 Create symbolic/hard links"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}chmod{reset}     - 🔐 Change file permissions"
+    )?;
+    writeln!(out, "  {yellow}chown{reset}     - 👤 Change file ownership")?;
+    writeln!(
+        out,
+        "  {yellow}find{reset}      - 🔍 Advanced file search with patterns"
+    )?;
+    writeln!(out, "  {yellow}locate{reset}    - ⚡ Fast file location")?;
+    writeln!(out, "  {yellow}du{reset}        - 📊 Disk usage analysis")?;
+    writeln!(
+        out,
+        "  {yellow}df{reset}        - 💿 Filesystem disk space info"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}stat{reset}      - 📋 Detailed file statistics"
+    )?;
+    writeln!(out)?;
 
     // Text Processing
-    println!("{coral}💬 TEXT PROCESSING & DATA MANIPULATION{reset}");
-    println!("  {yellow}cat{reset}       - 📖 Display file contents beautifully");
-    println!("  {yellow}echo{reset}      - 🗨️  Output text with style options");
-    println!("  {yellow}head{reset}      - 📄 Display first lines of files");
-    println!("  {yellow}tail{reset}      - 📄 Display last lines (with follow)");
-    println!("  {yellow}wc{reset}        - 📏 Count lines, words, characters");
-    println!("  {yellow}uniq{reset}      - 🎯 Remove or count duplicate lines");
-    println!("  {yellow}cut{reset}       - ✂️  Extract columns from text");
-    println!("  {yellow}tr{reset}        - 🔄 Translate/transform characters");
-    println!("  {yellow}tee{reset}       - 🔀 Split output to file and stdout");
-    println!("  {yellow}sed{reset}       - ✏️  Stream editor for filtering");
-    println!("  {yellow}awk{reset}       - 🧮 Pattern scanning and processing");
-    println!("  {yellow}sort{reset}      - 📊 Sort lines with various options");
-    println!("  {yellow}join{reset}      - 🔗 Join lines from two files");
-    println!("  {yellow}paste{reset}     - 📋 Merge lines from files");
-    println!("  {yellow}split{reset}     - ✂️  Split files into pieces");
-    println!("  {yellow}comm{reset}      - 🔍 Compare two sorted files");
-    println!("  {yellow}diff{reset}      - 📊 Show differences between files");
-    println!("  {yellow}patch{reset}     - 🩹 Apply patches to files");
-    println!("  {yellow}grep{reset}      - 🔍 Search text patterns with colors");
-    println!("  {yellow}egrep{reset}     - 🔍 Extended regular expressions");
-    println!("  {yellow}fgrep{reset}     - 🔍 Fixed string search");
-    println!();
+    writeln!(out, "{coral}💬 TEXT PROCESSING & DATA MANIPULATION{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}cat{reset}       - 📖 Display file contents beautifully"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}echo{reset}      - 🗨️  Output text with style options"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}head{reset}      - 📄 Display first lines of files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}tail{reset}      - 📄 Display last lines (with follow)"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}wc{reset}        - 📏 Count lines, words, characters"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}uniq{reset}      - 🎯 Remove or count duplicate lines"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}cut{reset}       - ✂️  Extract columns from text"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}tr{reset}        - 🔄 Translate/transform characters"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}tee{reset}       - 🔀 Split output to file and stdout"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}sed{reset}       - ✏️  Stream editor for filtering"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}awk{reset}       - 🧮 Pattern scanning and processing"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}sort{reset}      - 📊 Sort lines with various options"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}join{reset}      - 🔗 Join lines from two files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}paste{reset}     - 📋 Merge lines from files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}split{reset}     - ✂️  Split files into pieces"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}comm{reset}      - 🔍 Compare two sorted files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}diff{reset}      - 📊 Show differences between files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}patch{reset}     - 🩹 Apply patches to files"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}grep{reset}      - 🔍 Search text patterns with colors"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}egrep{reset}     - 🔍 Extended regular expressions"
+    )?;
+    writeln!(out, "  {yellow}fgrep{reset}     - 🔍 Fixed string search")?;
+    writeln!(out)?;
 
     // System Monitoring
-    println!("{green}⚙️  SYSTEM MONITORING & PROCESS MANAGEMENT{reset}");
-    println!("  {yellow}ps{reset}        - 📋 List running processes");
-    println!("  {yellow}top{reset}       - 📊 Real-time process monitor");
-    println!("  {yellow}htop{reset}      - 🌈 Enhanced interactive monitor");
-    println!("  {yellow}kill{reset}      - ⚡ Terminate processes by PID");
-    println!("  {yellow}killall{reset}   - ⚡ Kill processes by name");
-    println!("  {yellow}pgrep{reset}     - 🔍 Find processes by pattern");
-    println!("  {yellow}pkill{reset}     - ⚡ Kill processes by pattern");
-    println!("  {yellow}jobs{reset}      - 💼 Display active jobs");
-    println!("  {yellow}bg{reset}        - 🔙 Put jobs in background");
-    println!("  {yellow}fg{reset}        - 🔜 Bring jobs to foreground");
-    println!("  {yellow}nohup{reset}     - 🛡️  Run commands persistently");
-    println!("  {yellow}disown{reset}    - 🚫 Remove jobs from table");
-    println!("  {yellow}free{reset}      - 💾 Display memory usage");
-    println!("  {yellow}uptime{reset}    - ⏰ Show system uptime and load");
-    println!("  {yellow}uname{reset}     - 💻 System information display");
-    println!("  {yellow}whoami{reset}    - 👤 Current username");
-    println!("  {yellow}who{reset}       - 👥 Show logged-in users");
-    println!("  {yellow}id{reset}        - 🆔 User and group IDs");
-    println!("  {yellow}groups{reset}    - 👥 Show user groups");
-    println!();
+    writeln!(
+        out,
+        "{green}⚙️  SYSTEM MONITORING & PROCESS MANAGEMENT{reset}"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}ps{reset}        - 📋 List running processes"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}top{reset}       - 📊 Real-time process monitor"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}htop{reset}      - 🌈 Enhanced interactive monitor"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}kill{reset}      - ⚡ Terminate processes by PID"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}killall{reset}   - ⚡ Kill processes by name"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}pgrep{reset}     - 🔍 Find processes by pattern"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}pkill{reset}     - ⚡ Kill processes by pattern"
+    )?;
+    writeln!(out, "  {yellow}jobs{reset}      - 💼 Display active jobs")?;
+    writeln!(
+        out,
+        "  {yellow}bg{reset}        - 🔙 Put jobs in background"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}fg{reset}        - 🔜 Bring jobs to foreground"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}nohup{reset}     - 🛡️  Run commands persistently"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}disown{reset}    - 🚫 Remove jobs from table"
+    )?;
+    writeln!(out, "  {yellow}free{reset}      - 💾 Display memory usage")?;
+    writeln!(
+        out,
+        "  {yellow}uptime{reset}    - ⏰ Show system uptime and load"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}uname{reset}     - 💻 System information display"
+    )?;
+    writeln!(out, "  {yellow}whoami{reset}    - 👤 Current username")?;
+    writeln!(out, "  {yellow}who{reset}       - 👥 Show logged-in users")?;
+    writeln!(out, "  {yellow}id{reset}        - 🆔 User and group IDs")?;
+    writeln!(out, "  {yellow}groups{reset}    - 👥 Show user groups")?;
+    writeln!(out)?;
 
     // Network Tools
-    println!("{blue}🌐 NETWORK TOOLS & CONNECTIVITY{reset}");
-    println!("  {yellow}ping{reset}      - 🏓 Test network connectivity");
-    println!("  {yellow}curl{reset}      - 🌐 HTTP/HTTPS client tool");
-    println!("  {yellow}wget{reset}      - ⬇️  Download files from web");
-    println!("  {yellow}nc{reset}        - 🔌 Network swiss army knife");
-    println!("  {yellow}netcat{reset}    - 🔌 Advanced network utility");
-    println!("  {yellow}ssh{reset}       - 🔐 Secure shell connection");
-    println!("  {yellow}scp{reset}       - 📁 Secure file copy");
-    println!("  {yellow}rsync{reset}     - 🔄 Efficient file synchronization");
-    println!("  {yellow}ftp{reset}       - 📁 File transfer protocol");
-    println!("  {yellow}telnet{reset}    - 📞 Remote terminal access");
-    println!("  {yellow}host{reset}      - 🌐 DNS lookup utility");
-    println!("  {yellow}nslookup{reset}  - 🌐 Interactive DNS lookup");
-    println!("  {yellow}dig{reset}       - 🌐 Advanced DNS lookup");
-    println!("  {yellow}traceroute{reset} - 🗺️  Trace network route");
-    println!("  {yellow}netstat{reset}   - 🌐 Network statistics");
-    println!("  {yellow}ss{reset}        - 🌐 Socket statistics");
-    println!();
+    writeln!(out, "{blue}🌐 NETWORK TOOLS & CONNECTIVITY{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}ping{reset}      - 🏓 Test network connectivity"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}curl{reset}      - 🌐 HTTP/HTTPS client tool"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}wget{reset}      - ⬇️  Download files from web"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}nc{reset}        - 🔌 Network swiss army knife"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}netcat{reset}    - 🔌 Advanced network utility"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}ssh{reset}       - 🔐 Secure shell connection"
+    )?;
+    writeln!(out, "  {yellow}scp{reset}       - 📁 Secure file copy")?;
+    writeln!(
+        out,
+        "  {yellow}rsync{reset}     - 🔄 Efficient file synchronization"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}ftp{reset}       - 📁 File transfer protocol"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}telnet{reset}    - 📞 Remote terminal access"
+    )?;
+    writeln!(out, "  {yellow}host{reset}      - 🌐 DNS lookup utility")?;
+    writeln!(
+        out,
+        "  {yellow}nslookup{reset}  - 🌐 Interactive DNS lookup"
+    )?;
+    writeln!(out, "  {yellow}dig{reset}       - 🌐 Advanced DNS lookup")?;
+    writeln!(out, "  {yellow}traceroute{reset} - 🗺️  Trace network route")?;
+    writeln!(out, "  {yellow}netstat{reset}   - 🌐 Network statistics")?;
+    writeln!(out, "  {yellow}ss{reset}        - 🌐 Socket statistics")?;
+    writeln!(out)?;
 
     // Archive & Compression
-    println!("{orange}📦 ARCHIVE & COMPRESSION TOOLS{reset}");
-    println!("  {yellow}tar{reset}       - 📦 Create/extract tape archives");
-    println!("  {yellow}zip{reset}       - 📁 Create ZIP archives");
-    println!("  {yellow}unzip{reset}     - 📂 Extract ZIP archives");
-    println!("  {yellow}gzip{reset}      - 🗜️  GZIP compression");
-    println!("  {yellow}gunzip{reset}    - 📂 GZIP decompression");
-    println!("  {yellow}xz{reset}        - 🗜️  XZ compression (high ratio)");
-    println!("  {yellow}unxz{reset}      - 📂 XZ decompression");
-    println!("  {yellow}zstd{reset}      - ⚡ Zstandard compression (fast)");
-    println!("  {yellow}unzstd{reset}    - 📂 Zstandard decompression");
-    println!("  {yellow}bzip2{reset}     - 🗜️  BZIP2 compression");
-    println!("  {yellow}bunzip2{reset}   - 📂 BZIP2 decompression");
-    println!("  {yellow}7z{reset}        - 📁 7-Zip archive utility");
-    println!();
+    writeln!(out, "{orange}📦 ARCHIVE & COMPRESSION TOOLS{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}tar{reset}       - 📦 Create/extract tape archives"
+    )?;
+    writeln!(out, "  {yellow}zip{reset}       - 📁 Create ZIP archives")?;
+    writeln!(out, "  {yellow}unzip{reset}     - 📂 Extract ZIP archives")?;
+    writeln!(out, "  {yellow}gzip{reset}      - 🗜️  GZIP compression")?;
+    writeln!(out, "  {yellow}gunzip{reset}    - 📂 GZIP decompression")?;
+    writeln!(
+        out,
+        "  {yellow}xz{reset}        - 🗜️  XZ compression (high ratio)"
+    )?;
+    writeln!(out, "  {yellow}unxz{reset}      - 📂 XZ decompression")?;
+    writeln!(
+        out,
+        "  {yellow}zstd{reset}      - ⚡ Zstandard compression (fast)"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}unzstd{reset}    - 📂 Zstandard decompression"
+    )?;
+    writeln!(out, "  {yellow}bzip2{reset}     - 🗜️  BZIP2 compression")?;
+    writeln!(out, "  {yellow}bunzip2{reset}   - 📂 BZIP2 decompression")?;
+    writeln!(out, "  {yellow}7z{reset}        - 📁 7-Zip archive utility")?;
+    writeln!(out)?;
 
     // Shell Features
-    println!("{pink}🔧 SHELL FEATURES & ENVIRONMENT{reset}");
-    println!("  {yellow}alias{reset}     - 🔗 Create command shortcuts");
-    println!("  {yellow}unalias{reset}   - 🚫 Remove command aliases");
-    println!("  {yellow}history{reset}   - 📚 Command history management");
-    println!("  {yellow}export{reset}    - 🔄 Set environment variables");
-    println!("  {yellow}unset{reset}     - 🗑️  Remove variables");
-    println!("  {yellow}env{reset}       - 🌍 Show/modify environment");
-    println!("  {yellow}set{reset}       - ⚙️  Set shell options");
-    println!("  {yellow}declare{reset}   - 📋 Declare variables/functions");
-    println!("  {yellow}which{reset}     - 🔍 Locate command files");
-    println!("  {yellow}type{reset}      - 🔍 Show command type");
-    println!("  {yellow}builtin{reset}   - 🏠 Execute builtin commands");
-    println!();
+    writeln!(out, "{pink}🔧 SHELL FEATURES & ENVIRONMENT{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}alias{reset}     - 🔗 Create command shortcuts"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}unalias{reset}   - 🚫 Remove command aliases"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}history{reset}   - 📚 Command history management"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}export{reset}    - 🔄 Set environment variables"
+    )?;
+    writeln!(out, "  {yellow}unset{reset}     - 🗑️  Remove variables")?;
+    writeln!(
+        out,
+        "  {yellow}env{reset}       - 🌍 Show/modify environment"
+    )?;
+    writeln!(out, "  {yellow}set{reset}       - ⚙️  Set shell options")?;
+    writeln!(
+        out,
+        "  {yellow}declare{reset}   - 📋 Declare variables/functions"
+    )?;
+    writeln!(out, "  {yellow}which{reset}     - 🔍 Locate command files")?;
+    writeln!(out, "  {yellow}type{reset}      - 🔍 Show command type")?;
+    writeln!(
+        out,
+        "  {yellow}builtin{reset}   - 🏠 Execute builtin commands"
+    )?;
+    writeln!(out)?;
 
     // Utilities
-    println!("{lime}🛠️  SYSTEM UTILITIES & TOOLS{reset}");
-    println!("  {yellow}sleep{reset}     - 😴 Pause for specified time");
-    println!("  {yellow}timeout{reset}   - ⏲️  Run command with timeout");
-    println!("  {yellow}yes{reset}       - ♻️  Repeat string infinitely");
-    println!("  {yellow}seq{reset}       - 🔢 Generate number sequences");
-    println!("  {yellow}date{reset}      - 📅 Display/set system date");
-    println!("  {yellow}cal{reset}       - 📅 Display calendar");
-    println!("  {yellow}bc{reset}        - 🧮 Command-line calculator");
-    println!("  {yellow}expr{reset}      - 🧮 Evaluate expressions");
-    println!("  {yellow}true{reset}      - ✅ Always return success");
-    println!("  {yellow}false{reset}     - ❌ Always return failure");
-    println!("  {yellow}test{reset}      - 🧪 Evaluate conditional expressions");
-    println!("  {yellow}clear{reset}     - 🧹 Clear terminal screen");
-    println!("  {yellow}reset{reset}     - 🔄 Reset terminal to initial state");
-    println!();
-
-    println!("{lavender}💡 TIPS:{reset}");
-    println!("  • Type {yellow}help <command>{reset} for detailed information");
-    println!("  • Use {yellow}Tab{reset} for command completion");
-    println!("  • Press {yellow}Ctrl+C{reset} to interrupt commands");
-    println!("  • Use {yellow}man <command>{reset} for full manual pages");
-    println!();
-
-    println!("{cyan}🎨 UI Features:{reset}");
-    println!("  • {green}Syntax highlighting{reset} for commands");
-    println!("  • {blue}Smart completion{reset} with context");
-    println!("  • {purple}Beautiful file listings{reset} with icons");
-    println!("  • {coral}Colorized output{reset} for readability");
-    println!();
+    writeln!(out, "{lime}🛠️  SYSTEM UTILITIES & TOOLS{reset}")?;
+    writeln!(
+        out,
+        "  {yellow}sleep{reset}     - 😴 Pause for specified time"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}timeout{reset}   - ⏲️  Run command with timeout"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}yes{reset}       - ♻️  Repeat string infinitely"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}seq{reset}       - 🔢 Generate number sequences"
+    )?;
+    writeln!(
+        out,
+        "  {yellow}date{reset}      - 📅 Display/set system date"
+    )?;
+    writeln!(out, "  {yellow}cal{reset}       - 📅 Display calendar")?;
+    writeln!(
+        out,
+        "  {yellow}bc{reset}        - 🧮 Command-line calculator"
+    )?;
+    writeln!(out, "  {yellow}expr{reset}      - 🧮 Evaluate expressions")?;
+    writeln!(out, "  {yellow}true{reset}      - ✅ Always return success")?;
+    writeln!(out, "  {yellow}false{reset}     - ❌ Always return failure")?;
+    writeln!(
+        out,
+        "  {yellow}test{reset}      - 🧪 Evaluate conditional expressions"
+    )?;
+    writeln!(out, "  {yellow}clear{reset}     - 🧹 Clear terminal screen")?;
+    writeln!(
+        out,
+        "  {yellow}reset{reset}     - 🔄 Reset terminal to initial state"
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "{lavender}💡 TIPS:{reset}")?;
+    writeln!(
+        out,
+        "  • Type {yellow}help <command>{reset} for detailed information"
+    )?;
+    writeln!(out, "  • Use {yellow}Tab{reset} for command completion")?;
+    writeln!(out, "  • Press {yellow}Ctrl+C{reset} to interrupt commands")?;
+    writeln!(
+        out,
+        "  • Use {yellow}man <command>{reset} for full manual pages"
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "{cyan}🎨 UI Features:{reset}")?;
+    writeln!(out, "  • {green}Syntax highlighting{reset} for commands")?;
+    writeln!(out, "  • {blue}Smart completion{reset} with context")?;
+    writeln!(out, "  • {purple}Beautiful file listings{reset} with icons")?;
+    writeln!(out, "  • {coral}Colorized output{reset} for readability")?;
+    writeln!(out)?;
+    Ok(())
 }
 
 fn show_stylish_command_help(command: &str) {
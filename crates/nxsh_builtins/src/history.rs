@@ -2,6 +2,7 @@
 
 use nxsh_core::error::{ErrorKind, InternalErrorKind, IoErrorKind, RuntimeErrorKind, ShellError};
 use nxsh_core::{context::ShellContext, Builtin, ExecutionResult, ShellResult};
+use nxsh_ui::pager::{self, PagerOptions};
 use std::io::Write;
 
 /// The `history` builtin command implementation
@@ -123,7 +124,17 @@ impl HistoryCommand {
                 output.push('\n');
             }
 
-            ctx.stdout.write(output.as_bytes()).map_err(|e| {
+            // Long histories go through the pager on an interactive terminal
+            let written = if ctx.is_interactive() && pager::should_page(&output) {
+                let options = PagerOptions {
+                    name: Some("history".to_string()),
+                    ..Default::default()
+                };
+                pager::page(&output, options, &mut *ctx.stdout)
+            } else {
+                ctx.stdout.write_all(output.as_bytes())
+            };
+            written.map_err(|e| {
                 ShellError::new(
                     ErrorKind::IoError(IoErrorKind::FileWriteError),
                     format!("Failed to write output: {e}"),
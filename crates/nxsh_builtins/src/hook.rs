@@ -0,0 +1,153 @@
+//! `hook` builtin - run shell code on shell events
+//!
+//! Syntax:
+//!   hook add EVENT COMMAND...        # run COMMAND when EVENT fires
+//!   hook remove EVENT [COMMAND...]   # remove that hook, or all hooks on EVENT
+//!   hook [list [EVENT...]]           # print hooks as `hook add` commands
+//!   hook events                      # list the events
+//!
+//! Events are `pre_command` and `post_command` around each command line,
+//! `cwd_changed` after a command line that changed directory,
+//! `prompt_render` before the prompt is drawn and `job_finished` for each
+//! background job reported as done. Hook code finds the event in
+//! `HOOK_EVENT` and its details in `HOOK_COMMAND`, `HOOK_STATUS`,
+//! `HOOK_DURATION_MS`, `HOOK_OLDPWD`, `HOOK_PWD` and `HOOK_JOB`, and runs
+//! without firing hooks itself. Adding a hook twice keeps one, so `~/.nxshrc`
+//! can add hooks unconditionally. A direnv-like setup:
+//!
+//!   hook add cwd_changed '[ -f .envrc ] && source .envrc'
+//!
+//! Plugins add hooks from their manifest; `hook list` shows them as
+//! comments and `hook remove` takes them by command name. The registry lives
+//! in `ShellContext::hooks`; the executor runs the hooks.
+//!
+//! Exit status is 0 on success, 1 when there is no hook to remove and 2 on
+//! a usage error.
+
+use crate::common::{shell_quote, usage_error};
+use nxsh_core::context::ShellContext;
+use nxsh_core::hooks::{HookAction, HookKind};
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+
+/// The `hook` builtin command implementation
+pub struct HookCommand;
+
+impl Builtin for HookCommand {
+    fn name(&self) -> &'static str {
+        "hook"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run commands on shell events"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run COMMAND before or after each command line, when the working directory \
+         changes, before the prompt is drawn or when a background job finishes."
+    }
+
+    fn usage(&self) -> &'static str {
+        "hook add EVENT COMMAND... | hook remove EVENT [COMMAND...] | hook list [EVENT...] | \
+         hook events"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run commands on shell events. Use 'hook events' to list the events."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // hook modifies the shell's hook registry
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let Some((subcommand, rest)) = args.split_first() else {
+            return Ok(list(ctx, &[]));
+        };
+        match subcommand.as_str() {
+            "add" => {
+                let [event, command @ ..] = rest else {
+                    return Ok(usage_error("hook: usage: hook add EVENT COMMAND..."));
+                };
+                let kind = match parse_event(event) {
+                    Ok(kind) => kind,
+                    Err(result) => return Ok(result),
+                };
+                if command.is_empty() {
+                    return Ok(usage_error(&format!("hook: {event}: missing command")));
+                }
+                ctx.hooks.add_script(kind, &command.join(" "));
+                Ok(ExecutionResult::success(0))
+            }
+            "remove" | "rm" => {
+                let [event, command @ ..] = rest else {
+                    return Ok(usage_error("hook: usage: hook remove EVENT [COMMAND...]"));
+                };
+                let kind = match parse_event(event) {
+                    Ok(kind) => kind,
+                    Err(result) => return Ok(result),
+                };
+                let command = command.join(" ");
+                let name = (!command.is_empty()).then_some(command.as_str());
+                if ctx.hooks.remove(kind, name) == 0 && name.is_some() {
+                    return Ok(ExecutionResult::failure(1)
+                        .with_error(format!("hook: {event}: no such hook\n").into_bytes()));
+                }
+                Ok(ExecutionResult::success(0))
+            }
+            "list" | "ls" => Ok(list(ctx, rest)),
+            "events" => {
+                let mut out = String::new();
+                for kind in HookKind::ALL {
+                    out.push_str(kind.name());
+                    out.push('\n');
+                }
+                Ok(ExecutionResult::success(0).with_output(out.into_bytes()))
+            }
+            other => Ok(usage_error(&format!("hook: {other}: invalid subcommand"))),
+        }
+    }
+}
+
+impl HookCommand {
+    /// Create a new hook command instance
+    pub fn new() -> Self {
+        HookCommand
+    }
+}
+
+impl Default for HookCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `hook list`: script hooks as commands that would add them again, and
+/// plugin hooks as comments
+fn list(ctx: &ShellContext, events: &[String]) -> ExecutionResult {
+    let mut wanted = Vec::new();
+    for event in events {
+        match parse_event(event) {
+            Ok(kind) => wanted.push(kind),
+            Err(result) => return result,
+        }
+    }
+    let mut out = String::new();
+    for hook in ctx.hooks.list(None) {
+        if !wanted.is_empty() && !wanted.contains(&hook.kind) {
+            continue;
+        }
+        match &hook.action {
+            HookAction::Script(code) => {
+                out.push_str(&format!("hook add {} {}\n", hook.kind, shell_quote(code)));
+            }
+            HookAction::Callback { owner, name, .. } => {
+                out.push_str(&format!("# hook {} {name} from {owner}\n", hook.kind));
+            }
+        }
+    }
+    ExecutionResult::success(0).with_output(out.into_bytes())
+}
+
+fn parse_event(event: &str) -> Result<HookKind, ExecutionResult> {
+    HookKind::parse(event).ok_or_else(|| usage_error(&format!("hook: {event}: no such event")))
+}
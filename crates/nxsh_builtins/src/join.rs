@@ -1,97 +1,498 @@
-//! `join` command  Ecombine two text files on a common field (relational join).
+//! `join` builtin - join lines of two files on a common field
 //!
-//! Minimal subset implemented:
-//!   join FILE1 FILE2
-//!   • Assumes inputs are sorted on the join field.
-//!   • Join field is the first whitespace-separated field in each line.
-//!   • Output format: key TAB line1_rest TAB line2_rest
-//!   • Lines with unmatched keys are skipped (inner join).
-//!   • No options (-1, -2, -o, -a, -e, etc.) are supported yet.
-//!   • FILE of "-" refers to STDIN (only for FILE1; FILE2 must be path to avoid
-//!     consuming the same STDIN twice).
+//! Syntax:
+//!   join [-i] [-t CHAR] [-1 FIELD] [-2 FIELD] [-j FIELD] [-a FILENUM] [-v FILENUM]
+//!        [-e EMPTY] [-o FORMAT] [--header] [--check-order | --nocheck-order] FILE1 FILE2
 //!
-//! This covers the common case of joining two pre-sorted files by their first
-//! column.
+//! Both files must be sorted on their join field, the first by default.
+//! Every pair of lines with equal join fields produces one output line: the
+//! join field, then the other fields of FILE1, then those of FILE2. Fields
+//! are separated by runs of blanks, or by CHAR with `-t`, and written out
+//! separated by a space or CHAR. `-a` also prints the lines of FILENUM that
+//! have no partner, `-v` prints only those. `-o` lists the fields to print
+//! as FILENUM.FIELD, or 0 for the join field, separated by commas or
+//! blanks; `-o auto` takes the layout of the first lines, and `-e` stands
+//! in for fields a line lacks.
+//!
+//! The exit status is 0 on success and 1 when a FILE cannot be read or is
+//! not sorted.
 
-use anyhow::{anyhow, Result};
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
 use std::path::Path;
 
-pub fn join_cli(args: &[String]) -> Result<()> {
-    if args.len() < 2 {
-        return Err(anyhow!("join: missing file operands"));
+/// The `join` builtin command implementation
+pub struct JoinCommand;
+
+impl Builtin for JoinCommand {
+    fn name(&self) -> &'static str {
+        "join"
     }
-    let file1 = &args[0];
-    let file2 = &args[1];
 
-    let reader1: Box<dyn BufRead> = if file1 == "-" {
-        Box::new(BufReader::new(io::stdin()))
-    } else {
-        Box::new(BufReader::new(File::open(Path::new(file1))?))
-    };
-    let reader2: Box<dyn BufRead> = Box::new(BufReader::new(File::open(Path::new(file2))?));
+    fn synopsis(&self) -> &'static str {
+        "Join lines of two files on a common field"
+    }
+
+    fn description(&self) -> &'static str {
+        "For each pair of lines of the sorted files FILE1 and FILE2 with equal join \
+         fields, write a line made of the join field and the other fields of both."
+    }
+
+    fn usage(&self) -> &'static str {
+        "join [-i] [-t CHAR] [-1 FIELD] [-2 FIELD] [-a FILENUM] [-v FILENUM] [-e EMPTY] \
+         [-o FORMAT] FILE1 FILE2"
+    }
+
+    fn help(&self) -> &'static str {
+        "Join lines of two files on a common field. Use 'join -t, -a1 a.csv b.csv' for a \
+         left outer join of CSV files."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run join for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// One entry of `-o FORMAT`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutField {
+    /// `0`, the join field
+    Key,
+    /// `FILENUM.FIELD`, with both counted from 0
+    Field(usize, usize),
+}
 
-    join_streams(reader1, reader2)?;
-    Ok(())
+#[derive(Debug)]
+struct Options {
+    ignore_case: bool,
+    separator: Option<u8>,
+    /// The join field of each file, counted from 0
+    fields: [usize; 2],
+    unpaired: [bool; 2],
+    only_unpaired: bool,
+    empty: Option<Vec<u8>>,
+    /// The `-o` list; `Some(empty)` for `-o auto`
+    format: Option<Vec<OutField>>,
+    header: bool,
+    check_order: bool,
+    files: [String; 2],
 }
 
-fn split_key(line: &str) -> (&str, &str) {
-    if let Some(idx) = line.find(char::is_whitespace) {
-        let (k, rest) = line.split_at(idx);
-        let rest_trim = rest.trim_start_matches(char::is_whitespace);
-        (k, rest_trim)
-    } else {
-        (line.trim_end_matches('\n'), "")
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            ignore_case: false,
+            separator: None,
+            fields: [0, 0],
+            unpaired: [false, false],
+            only_unpaired: false,
+            empty: None,
+            format: None,
+            header: false,
+            check_order: true,
+            files: Default::default(),
+        };
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let (flag, attached) = match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "-i" | "--ignore-case" => {
+                    options.ignore_case = true;
+                    continue;
+                }
+                "--header" => {
+                    options.header = true;
+                    continue;
+                }
+                "--check-order" => {
+                    options.check_order = true;
+                    continue;
+                }
+                "--nocheck-order" => {
+                    options.check_order = false;
+                    continue;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    let flag = short[1..].chars().next().unwrap_or('-');
+                    (flag, &short[1 + flag.len_utf8()..])
+                }
+                _ => {
+                    operands.push(arg.clone());
+                    continue;
+                }
+            };
+            if !"t12javeo".contains(flag) {
+                return Err(format!("invalid option -- '{flag}'"));
+            }
+            let value = if attached.is_empty() {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?
+            } else {
+                attached.to_string()
+            };
+            options.set(flag, &value)?;
+        }
+        match <[String; 2]>::try_from(operands) {
+            Ok(files) => options.files = files,
+            Err(operands) if operands.len() < 2 => {
+                return Err(match operands.first() {
+                    Some(file) => format!("missing operand after '{file}'"),
+                    None => "missing operand".to_string(),
+                })
+            }
+            Err(operands) => return Err(format!("extra operand '{}'", operands[2])),
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, flag: char, value: &str) -> Result<(), String> {
+        let field = || match value.parse::<usize>() {
+            Ok(field) if field > 0 => Ok(field - 1),
+            _ => Err(format!("invalid field number: '{value}'")),
+        };
+        let file_number = || match value {
+            "1" => Ok(0),
+            "2" => Ok(1),
+            _ => Err(format!("invalid file number: '{value}'")),
+        };
+        match flag {
+            't' => match value.as_bytes() {
+                [separator] => self.separator = Some(*separator),
+                _ => return Err(format!("multi-character tab '{value}'")),
+            },
+            '1' => self.fields[0] = field()?,
+            '2' => self.fields[1] = field()?,
+            'j' => self.fields = [field()?; 2],
+            'a' => self.unpaired[file_number()?] = true,
+            'v' => {
+                self.unpaired[file_number()?] = true;
+                self.only_unpaired = true;
+            }
+            'e' => self.empty = Some(value.as_bytes().to_vec()),
+            _ if value == "auto" => self.format = Some(Vec::new()),
+            _ => {
+                let mut format = Vec::new();
+                for spec in value.split(|c: char| c == ',' || c.is_ascii_whitespace()) {
+                    format.push(match spec.split_once('.') {
+                        None if spec == "0" => OutField::Key,
+                        Some((file @ ("1" | "2"), field)) => match field.parse::<usize>() {
+                            Ok(field) if field > 0 => {
+                                OutField::Field(usize::from(file == "2"), field - 1)
+                            }
+                            _ => return Err(format!("invalid field number: '{field}'")),
+                        },
+                        _ => return Err(format!("invalid field specifier: '{spec}'")),
+                    });
+                }
+                self.format = Some(format);
+            }
+        }
+        Ok(())
+    }
+
+    /// The fields of LINE
+    fn split<'a>(&self, line: &'a [u8]) -> Vec<&'a [u8]> {
+        match self.separator {
+            Some(separator) => line.split(|&byte| byte == separator).collect(),
+            None => line
+                .split(|&byte| byte == b' ' || byte == b'\t')
+                .filter(|field| !field.is_empty())
+                .collect(),
+        }
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        if self.ignore_case {
+            a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase())
+        } else {
+            a.cmp(b)
+        }
     }
 }
 
-fn join_streams<R1: BufRead, R2: BufRead>(mut r1: R1, mut r2: R2) -> Result<()> {
-    let mut l1 = String::new();
-    let mut l2 = String::new();
+/// A line of one of the files split into fields
+struct Record<'a> {
+    line: &'a [u8],
+    fields: Vec<&'a [u8]>,
+    key: &'a [u8],
+}
 
-    let mut eof1 = r1.read_line(&mut l1)? == 0;
-    let mut eof2 = r2.read_line(&mut l2)? == 0;
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("join: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let mut inputs = Inputs::new(cwd, stdin);
+    let mut contents = Vec::new();
+    for name in &options.files {
+        match inputs.read(name) {
+            Ok(data) => contents.push(data),
+            Err(e) => {
+                outcome.stderr = format!("join: {name}: {}\n", io_message(&e)).into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+        }
+    }
+    let records: Vec<Vec<Record>> = contents
+        .iter()
+        .zip(options.fields)
+        .map(|(data, field)| {
+            lines(data)
+                .into_iter()
+                .map(|line| {
+                    let fields = options.split(line);
+                    let key = fields.get(field).copied().unwrap_or_default();
+                    Record { line, fields, key }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut join = Join {
+        options: &options,
+        format: options.format.clone(),
+        out: Vec::new(),
+    };
+    if join.format.as_ref().is_some_and(Vec::is_empty) {
+        join.format = Some(auto_format(&options, &records));
+    }
+
+    let skip = usize::from(options.header);
+    if options.header {
+        let (one, two) = (records[0].first(), records[1].first());
+        if let Some(first) = one.or(two) {
+            join.write(first.key, [one, two]);
+        }
+    }
+
+    if options.check_order {
+        for (file, name) in records.iter().zip(&options.files) {
+            let file = file.get(skip..).unwrap_or_default();
+            let disorder = file
+                .windows(2)
+                .position(|pair| options.compare(pair[0].key, pair[1].key) == Ordering::Greater);
+            if let Some(at) = disorder {
+                outcome.stderr.extend(
+                    format!(
+                        "join: {name}:{}: is not sorted: {}\n",
+                        at + 2 + skip,
+                        String::from_utf8_lossy(file[at + 1].line)
+                    )
+                    .as_bytes(),
+                );
+                outcome.status = 1;
+            }
+        }
+    }
+
+    let one = records[0].get(skip..).unwrap_or_default();
+    let two = records[1].get(skip..).unwrap_or_default();
+    let (mut i, mut j) = (0, 0);
+    while i < one.len() && j < two.len() {
+        match options.compare(one[i].key, two[j].key) {
+            Ordering::Less => {
+                join.unpaired(0, &one[i]);
+                i += 1;
+            }
+            Ordering::Greater => {
+                join.unpaired(1, &two[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                let group = |file: &[Record], start: usize| {
+                    start
+                        + file[start..]
+                            .iter()
+                            .take_while(|record| {
+                                options.compare(record.key, file[start].key) == Ordering::Equal
+                            })
+                            .count()
+                };
+                let (end_one, end_two) = (group(one, i), group(two, j));
+                if !options.only_unpaired {
+                    for left in &one[i..end_one] {
+                        for right in &two[j..end_two] {
+                            join.write(left.key, [Some(left), Some(right)]);
+                        }
+                    }
+                }
+                (i, j) = (end_one, end_two);
+            }
+        }
+    }
+    for record in &one[i..] {
+        join.unpaired(0, record);
+    }
+    for record in &two[j..] {
+        join.unpaired(1, record);
+    }
+    outcome.stdout = join.out;
+    outcome
+}
 
-    let mut out = io::stdout();
+/// The `-o auto` format: the join field, then every other field of the
+/// first line of each file
+fn auto_format(options: &Options, records: &[Vec<Record>]) -> Vec<OutField> {
+    let mut format = vec![OutField::Key];
+    for (file, join_field) in options.fields.into_iter().enumerate() {
+        let count = records[file]
+            .first()
+            .map_or(0, |record| record.fields.len());
+        format.extend(
+            (0..count)
+                .filter(|&field| field != join_field)
+                .map(|field| OutField::Field(file, field)),
+        );
+    }
+    format
+}
+
+/// Output lines being assembled
+struct Join<'a> {
+    options: &'a Options,
+    format: Option<Vec<OutField>>,
+    out: Vec<u8>,
+}
 
-    while !(eof1 || eof2) {
-        let (k1, rest1) = split_key(l1.trim_end_matches('\n'));
-        let (k2, rest2) = split_key(l2.trim_end_matches('\n'));
+impl Join<'_> {
+    fn unpaired(&mut self, file: usize, record: &Record) {
+        if self.options.unpaired[file] {
+            let mut pair = [None, None];
+            pair[file] = Some(record);
+            self.write(record.key, pair);
+        }
+    }
 
-        match k1.cmp(k2) {
-            std::cmp::Ordering::Equal => {
-                writeln!(out, "{k1}\t{rest1}\t{rest2}")?;
-                l2.clear();
-                eof2 = r2.read_line(&mut l2)? == 0;
+    /// Write the line for KEY joining PAIR, either of which may be missing
+    fn write(&mut self, key: &[u8], pair: [Option<&Record>; 2]) {
+        let empty = self.options.empty.as_deref().unwrap_or_default();
+        let mut fields: Vec<&[u8]> = Vec::new();
+        match &self.format {
+            Some(format) => {
+                for spec in format {
+                    fields.push(match *spec {
+                        OutField::Key => key,
+                        OutField::Field(file, field) => pair[file]
+                            .and_then(|record| record.fields.get(field).copied())
+                            .unwrap_or(empty),
+                    });
+                }
             }
-            std::cmp::Ordering::Less => {
-                l1.clear();
-                eof1 = r1.read_line(&mut l1)? == 0;
+            None => {
+                fields.push(key);
+                for (record, join_field) in pair.iter().zip(self.options.fields) {
+                    if let Some(record) = record {
+                        fields.extend(
+                            record
+                                .fields
+                                .iter()
+                                .enumerate()
+                                .filter(|&(field, _)| field != join_field)
+                                .map(|(_, &value)| value),
+                        );
+                    }
+                }
             }
-            std::cmp::Ordering::Greater => {
-                l2.clear();
-                eof2 = r2.read_line(&mut l2)? == 0;
+        }
+        let separator = self.options.separator.unwrap_or(b' ');
+        for (index, field) in fields.into_iter().enumerate() {
+            if index > 0 {
+                self.out.push(separator);
             }
+            self.out.extend_from_slice(field);
         }
+        self.out.push(b'\n');
     }
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+
+    fn join(args: &[&str], dir: &Path) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut io::empty());
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.status)
+    }
 
     #[test]
-    fn join_basic() {
-        let data1 = b"a 1\nb 2\nc 3\n";
-        let data2 = b"a X\nc Z\n";
-        // Execute join_streams; ensure it returns Ok.
-        join_streams(
-            BufReader::new(Cursor::new(&data1[..])),
-            BufReader::new(Cursor::new(&data2[..]))
-        ).unwrap();
+    fn joins_on_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "1 apple\n2 pear\n2 plum\n4 fig\n").unwrap();
+        std::fs::write(dir.path().join("b"), "2 green\n3 red\n4 brown\n").unwrap();
+        assert_eq!(
+            join(&["a", "b"], dir.path()),
+            ("2 pear green\n2 plum green\n4 fig brown\n".into(), 0)
+        );
+        assert_eq!(
+            join(&["-v", "1", "a", "b"], dir.path()),
+            ("1 apple\n".into(), 0)
+        );
+        assert_eq!(
+            join(&["-a2", "-e", "-", "-o", "0,1.2,2.2", "a", "b"], dir.path()),
+            (
+                "2 pear green\n2 plum green\n3 - red\n4 fig brown\n".into(),
+                0
+            )
+        );
+
+        std::fs::write(dir.path().join("c"), "2,green\n4,brown\n").unwrap();
+        std::fs::write(dir.path().join("d"), "x,2\ny,4\n").unwrap();
+        assert_eq!(
+            join(&["-t,", "-1", "1", "-2", "2", "c", "d"], dir.path()).0,
+            "2,green,x\n4,brown,y\n"
+        );
     }
-} 
 
+    #[test]
+    fn reports_unsorted_input() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "b\na\n").unwrap();
+        std::fs::write(dir.path().join("b"), "a\n").unwrap();
+        assert_eq!(join(&["a", "b"], dir.path()).1, 1);
+        assert_eq!(join(&["--nocheck-order", "a", "b"], dir.path()).1, 0);
+    }
+}
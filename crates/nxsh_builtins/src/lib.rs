@@ -7,7 +7,8 @@
 #![allow(unused_imports)]
 
 // Core Shell Features 🐚
-pub mod advanced_cui;
+pub mod abbr; // ✂️ Abbreviations expanded as you type
+pub mod advanced_cui; // 🖌 Advanced CUI components
 pub mod alias; // 🔗 Command aliases
 pub mod builtin; // 🛠️ Built-in command handler
 pub mod clear; // 🧹 Clear screen
@@ -16,32 +17,61 @@ pub mod common; // ⚙️ Shared types and helpers
 pub mod function; // 🔁 Shell functions handling
 pub mod help; // 📚 Help system
 pub mod history; // 📜 Command history
-pub mod universal_formatter; // 🖼️ Formatter used by beautiful UI // 🖌 Advanced CUI components
+pub mod hook; // 🪝 Commands run on shell events
+pub mod universal_formatter; // 🖼️ Formatter used by beautiful UI
 
 // File Operations 📁 (Confirmed existing files only)
+pub mod basename; // ✂️ Strip directories from names
 pub mod cd; // 📂 Change directory
 pub mod chgrp; // 👥 Change group
 pub mod chmod; // 🔐 Change permissions
 pub mod chown; // 👤 Change ownership
 pub mod cp; // 📄 Copy files
+pub mod dd; // 💽 Block copy and convert
 pub mod df; // 💾 Disk free space
+pub mod dirname; // 📂 Strip the last name component
+pub mod dirs; // 🗂️ Directory stack (dirs, pushd, popd)
 pub mod du; // 📊 Disk usage
+pub mod file; // 🔬 Identify file types
+pub mod find; // 🔎 Search directory trees
 pub mod ln; // 🔗 Create links
 pub mod ls; // 📋 List directory contents
 pub mod mkdir; // 📁 Create directories
+pub mod mktemp; // 🧪 Create temporary files
 pub mod mv; // 🔄 Move/rename files
 pub mod pwd; // 📍 Print working directory
+pub mod readlink; // 🔗 Print link targets
+pub mod realpath; // 🧭 Resolve absolute paths
 pub mod rm; // 🗑️ Remove files
-pub mod stat;
-pub mod touch; // ✋ Create/update files // ℹ️ File information
+pub mod safety; // 🛡️ Prompts and backups for rm, cp and mv
+pub mod stat; // ℹ️ File information
+pub mod sync_cmd; // 🔁 Incremental tree copies
+pub mod touch; // ✋ Create/update files
+pub mod transfer; // 📶 Progress of cp and mv copies
+pub mod trash; // 🗑️ Desktop trash
+pub mod tree; // 🌳 Directory trees
+pub mod xattr; // 🏷️ Extended attributes (getfattr, setfattr)
 
 // Text Processing 📝 (Confirmed existing files only)
+pub mod awk; // 🦅 Pattern scanning and processing
 pub mod cat; // 📖 Display file contents
+pub mod comm; // ⚖️ Compare sorted files
 pub mod cut; // ✂️ Extract columns
+pub mod diff; // 🔀 Compare files line by line
 pub mod echo; // 📢 Output text
+pub mod grep; // 🔍 Search text patterns
 pub mod head; // ⬆️ Show file beginning
+pub mod join; // 🔗 Join files on a field
+pub mod less; // 📖 Page through text
+pub mod nl; // 🔢 Number lines
+pub mod paste; // 📋 Merge lines of files
+pub mod rev; // ↔️ Reverse lines
+pub mod sed; // ✏️ Stream editor
 pub mod sort; // 📊 Sort text lines
+pub mod split; // 🪓 Split files into pieces
+pub mod tac; // 🔃 Reverse line order
 pub mod tail; // ⬇️ Show file end
+pub mod tee; // 🚰 Copy input to files
 pub mod tr; // 🔄 Translate characters
 pub mod uniq; // 🎯 Remove duplicates
 pub mod wc; // 📏 Count lines/words
@@ -52,31 +82,61 @@ pub mod fg; // ⬆️ Foreground processes
 pub mod free; // 🧠 Memory usage
 pub mod jobs; // 💼 Job control
 pub mod kill; // ⚡ Terminate processes
+pub mod pgrep; // 🔎 Find and signal processes by name
 pub mod ps; // 📋 Process status
 pub mod top; // 📊 Process monitor
 pub mod uptime; // ⏰ System uptime
+pub mod watch; // 👀 Re-run commands periodically
 pub mod whoami; // 👤 Current user
 
 // Network Tools 🌐 (Confirmed existing files only)
 pub mod curl; // 🌐 HTTP client
+pub mod dig; // 🔎 DNS queries
+pub mod nc; // 🔗 TCP and UDP connections
+pub mod netstat; // 🔌 Open sockets and their processes
 pub mod ping; // 🏓 Network ping
+#[cfg(feature = "remote")]
+pub mod scp; // 📦 Copies over SSH
+#[cfg(feature = "remote")]
+pub mod ssh; // 🔐 Remote login
+pub mod traceroute; // 🗺️ Routers on the way to a host
 pub mod wget; // 📥 File downloader
 
 // Shell Utilities 🔧 (Confirmed existing files only)
+pub mod conversions; // 🔀 JSON, YAML, TOML and CSV to and from structured values
 pub mod date; // 📅 Date and time
+pub mod declare; // 📝 Variables, arrays and attributes
 pub mod env; // 🌍 Environment variables
 pub mod export; // 📤 Export variables
 pub mod export_builtin; // 📤 Export variables (new implementation)
+pub mod factor; // 🔢 Prime factors
+pub mod getopts; // 🎛️ Script option parsing
+pub mod local; // 📌 Function-local variables
+pub mod query; // 🔎 Conditions, sort keys and aggregates for structured data
+pub mod read; // ⌨️ Read input into variables
+pub mod seq; // 🔢 Number sequences
+pub mod set; // ⚙️ Shell options and positional parameters
+pub mod shift; // ⬅️ Shift positional parameters
+pub mod shopt; // ⚙️ Optional shell behaviour
+pub mod shuf; // 🎲 Random permutations
 pub mod sleep; // 😴 Pause execution
+pub mod structured; // 🧮 Typed tables passed between pipeline stages
+pub mod test_builtin; // ⚖️ Conditional expressions (test and [)
+pub mod trap; // 🪤 Signal and exit traps
 pub mod true_cmd; // ✅ Success command (renamed to avoid Rust keyword)
-pub mod unalias;
+pub mod ulimit; // 📏 Resource limits
+pub mod umask; // 🎭 File creation mask
+pub mod unalias; // 🚫 Remove aliases
 pub mod uname; // 💻 System information
 pub mod unset; // 🚫 Remove variables
 pub mod which; // 🔍 Locate commands
-pub mod yes; // ♻️ Repeat output // 🚫 Remove aliases
+pub mod xargs; // 🧱 Build command lines from input
+pub mod yes; // ♻️ Repeat output
 
 // Archive & Compression 📦 (Confirmed existing files only)
 pub mod bzip2; // 🗜️ BZIP2 compression
+pub mod gzip; // 🗜️ GZIP compression
+pub mod tar; // 📦 Tape archives
 pub mod xz; // 🗜️ XZ compression
 pub mod zip; // 📦 ZIP archives
 
@@ -90,16 +150,22 @@ pub mod base64; // 🔤 Base64 encoding
 pub mod bc; // 🧮 Calculator
 pub mod cal; // 📅 Calendar
 pub mod cksum; // #️⃣ Checksum
+pub mod hashsum; // 🔏 MD5, SHA and BLAKE2 digests
 
 // System Control 🎛️ (Confirmed existing files only)
-pub mod eval;
+pub mod elevate; // 🛡️ Run commands with administrative rights (elevate, runas)
+pub mod eval; // 📜 Evaluate expressions
 pub mod exec; // 🚀 Execute commands
-pub mod exit; // 🚪 Exit shell // 📜 Evaluate expressions
+pub mod exit; // 🚪 Exit shell
+#[cfg(feature = "plugins")]
+pub mod plugin; // 🧩 Install and manage plugins
+pub mod sandbox; // 🧱 Run commands under sandbox profiles
 
 // File System Tools 🔧 (Additional existing modules)
 pub mod fsck; // 🔧 File system check
-pub mod logstats_builtin;
-pub mod mount; // 💾 Mount filesystems // 📈 Log statistics
+pub mod logstats_builtin; // 📈 Log statistics
+pub mod lsblk; // 🧱 Block devices
+pub mod mount; // 💾 Mount filesystems
 
 // Compression Tools 🗜️ (Additional existing modules)
 pub mod unzstd; // 🗜️ Zstandard decompression
@@ -107,9 +173,12 @@ pub mod zstd; // 🗜️ Zstandard compression
 pub mod zstd_impl; // 🧩 Internal Zstd implementation (encoder utilities)
 
 // System Time Tools ⏰ (Additional existing modules)
+#[cfg(feature = "scheduler")]
+pub mod schedule; // ⏲️ Jobs run later, once or on a cron schedule
 pub mod timedatectl; // ⏰ Time and date control
 
 // Variable Management Tools 📝 (Additional existing modules)
+pub mod printf; // 🖨️ Formatted output
 pub mod vars; // 📝 Variable operations (let, declare, printf)
 
 // Import all command execution functions
@@ -134,6 +203,7 @@ use crate::env::execute as env_execute;
 use crate::export::execute as export_execute;
 use crate::fg::execute as fg_execute;
 use crate::free::execute as free_execute;
+use crate::gzip::{execute as gzip_execute, gunzip_execute, zcat_execute};
 use crate::head::execute as head_execute;
 use crate::help::execute as help_execute;
 use crate::history::execute as history_execute;
@@ -177,6 +247,7 @@ use crate::exec::execute as exec_execute;
 use crate::exit::execute as exit_execute;
 use crate::fsck::execute as fsck_execute;
 use crate::logstats_builtin::execute as logstats_builtin_execute;
+use crate::printf::execute as printf_execute;
 use crate::smart_alias::execute as smart_alias_execute;
 use crate::timedatectl::execute_builtin as timedatectl_execute;
 use crate::ui_design::execute as ui_design_execute;
@@ -193,6 +264,8 @@ pub struct BuiltinCommand {
     pub description: String,
     pub usage: String,
     pub examples: Vec<String>,
+    /// Flags the command accepts, with a short description, for completion
+    pub flags: Vec<(String, String)>,
 }
 
 impl BuiltinCommand {
@@ -203,6 +276,7 @@ impl BuiltinCommand {
             description: description.to_string(),
             usage: usage.to_string(),
             examples: Vec::new(),
+            flags: Vec::new(),
         }
     }
 
@@ -210,6 +284,14 @@ impl BuiltinCommand {
         self.examples = examples.iter().map(|e| e.to_string()).collect();
         self
     }
+
+    pub fn with_flags(mut self, flags: &[(&str, &str)]) -> Self {
+        self.flags = flags
+            .iter()
+            .map(|(flag, description)| (flag.to_string(), description.to_string()))
+            .collect();
+        self
+    }
 }
 
 /// Function to check if a command is builtin
@@ -222,34 +304,41 @@ pub fn is_builtin(name: &str) -> bool {
         // File Operations 📁
         "ls" | "pwd" | "cd" | "touch" | "mkdir" | "cp" | "mv" | "rm" |
         "chmod" | "chown" | "chgrp" | "ln" | "du" | "df" | "stat" |
+        "basename" | "dirname" | "realpath" | "readlink" | "mktemp" | "file" | "dd" |
+        "tree" | "sync" | "rcp" | "getfattr" | "setfattr" | "trash" |
 
         // Text Processing 📝
-        "cat" | "echo" | "head" | "tail" | "cut" | "tr" | "uniq" | "wc" |
+        "cat" | "echo" | "head" | "tail" | "cut" | "tr" | "uniq" | "wc" | "diff" |
+        "tee" | "split" | "paste" | "join" | "comm" | "nl" | "tac" | "rev" |
+        "less" | "more" |
 
         // System Monitoring 📊
         "ps" | "kill" | "top" | "jobs" | "bg" | "fg" | "free" | "uptime" | "whoami" |
+        "watch" | "pgrep" | "pkill" |
 
         // Network Tools 🌐
-        "ping" | "curl" | "wget" |
+        "ping" | "curl" | "wget" | "netstat" | "dig" | "traceroute" | "tracert" | "nc" |
 
         // Shell Utilities 🔧
         "which" | "sleep" | "date" | "env" | "export" | "yes" | "true" | "uname" |
-        "unset" | "unalias" |
+        "unset" | "unalias" | "seq" | "shuf" | "factor" | "test" | "[" |
+        "ulimit" | "umask" |
 
         // Archive & Compression 📦
-        "bzip2" | "xz" | "zip" |
+        "bzip2" | "gzip" | "gunzip" | "zcat" | "xz" | "zip" |
 
         // Advanced Features 🎨
         // "beautiful_ls" | "smart_alias" | "ui_design" |
 
         // Text Utilities 📄
         "base64" | "bc" | "cal" | "cksum" |
+        "md5sum" | "sha1sum" | "sha256sum" | "sha512sum" | "b2sum" |
 
         // System Control 🎛️
-        "exec" | "exit" | "eval" |
+        "exec" | "exit" | "eval" | "elevate" | "runas" | "sandbox" |
 
         // File System Tools 🔧
-        "fsck" | "logstats" |
+        "fsck" | "logstats" | "mount" | "lsblk" |
 
         // Compression Tools 🗜️
         "zstd" | "unzstd" |
@@ -259,13 +348,31 @@ pub fn is_builtin(name: &str) -> bool {
 
         // Variable Management Tools 📝
         "let" | "declare" | "printf"
-    )
+    ) || (cfg!(feature = "remote") && matches!(name, "ssh" | "scp"))
+        || (cfg!(feature = "scheduler") && matches!(name, "schedule" | "at" | "cron"))
+        || (cfg!(feature = "plugins") && name == "plugin")
 }
 
 /// List all available built-in commands
 pub fn list_builtins() -> Vec<BuiltinCommand> {
     vec![
         // Core Shell Features 🐚
+        BuiltinCommand::new(
+            "abbr",
+            "🐚 Shell Features",
+            "Abbreviations expanded as you type",
+            "abbr [OPTIONS] NAME EXPANSION...",
+        )
+        .with_flags(&[
+            ("-a", "add an abbreviation"),
+            ("-e", "erase abbreviations"),
+            ("-r", "rename an abbreviation"),
+            ("-q", "test whether abbreviations exist"),
+            ("-l", "list abbreviation names"),
+            ("-s", "print abbreviations as commands"),
+            ("--position", "expand as a command or anywhere"),
+            ("--set-cursor", "leave the cursor at the % marker"),
+        ]),
         BuiltinCommand::new(
             "alias",
             "🐚 Shell Features",
@@ -295,6 +402,13 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "🐚 Shell Features",
             "Command history management",
             "history [OPTIONS]",
+        )
+        .with_flags(&[("-c", "clear the history"), ("-d", "delete an entry")]),
+        BuiltinCommand::new(
+            "hook",
+            "🐚 Shell Features",
+            "Commands run on shell events",
+            "hook add|remove|list|events [EVENT] [COMMAND...]",
         ),
         // File Operations 📁
         BuiltinCommand::new(
@@ -302,7 +416,24 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "📁 File Operations",
             "List directory contents",
             "ls [OPTIONS] [PATH...]",
-        ),
+        )
+        .with_flags(&[
+            ("-a", "include hidden entries"),
+            ("-l", "long listing"),
+            ("-h", "human-readable sizes"),
+            ("-R", "list subdirectories recursively"),
+            ("-r", "reverse the order"),
+            ("-d", "list directories themselves"),
+            ("--all", "include hidden entries"),
+            ("--almost-all", "hidden entries except . and .."),
+            ("--classify", "append a type indicator"),
+            ("--color", "colorize the output"),
+            ("--human-readable", "human-readable sizes"),
+            ("--recursive", "list subdirectories recursively"),
+            ("--reverse", "reverse the order"),
+            ("--group-directories-first", "list directories before files"),
+            ("-@", "mark and list extended attributes and ACLs"),
+        ]),
         BuiltinCommand::new(
             "pwd",
             "📁 File Operations",
@@ -313,38 +444,135 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "cd",
             "📁 File Operations",
             "Change directory",
-            "cd [DIRECTORY]",
-        ),
+            "cd [-L|-P] [DIRECTORY | -]",
+        )
+        .with_flags(&[
+            ("-L", "follow symbolic links"),
+            ("-P", "use the physical directory structure"),
+        ]),
+        BuiltinCommand::new(
+            "pushd",
+            "📁 File Operations",
+            "Save the directory and change to another",
+            "pushd [-n] [DIRECTORY | +N | -N]",
+        )
+        .with_flags(&[("-n", "only change the stack, not the directory")]),
+        BuiltinCommand::new(
+            "popd",
+            "📁 File Operations",
+            "Return to the last saved directory",
+            "popd [-n] [+N | -N]",
+        )
+        .with_flags(&[("-n", "only change the stack, not the directory")]),
+        BuiltinCommand::new(
+            "dirs",
+            "📁 File Operations",
+            "Display the directory stack",
+            "dirs [-clpv] [+N | -N]",
+        )
+        .with_flags(&[
+            ("-c", "clear the directory stack"),
+            ("-l", "show full paths instead of ~"),
+            ("-p", "print one entry per line"),
+            ("-v", "print one numbered entry per line"),
+        ]),
         BuiltinCommand::new(
             "touch",
             "📁 File Operations",
             "Create/update files",
             "touch [OPTIONS] FILE...",
-        ),
+        )
+        .with_flags(&[
+            ("-a", "change only the access time"),
+            ("-m", "change only the modification time"),
+            ("-c", "do not create files"),
+            ("-d", "use this date"),
+            ("-r", "use this file's times"),
+            ("--no-create", "do not create files"),
+            ("--date", "use this date"),
+            ("--reference", "use this file's times"),
+        ]),
         BuiltinCommand::new(
             "mkdir",
             "📁 File Operations",
             "Create directories",
             "mkdir [OPTIONS] DIRECTORY...",
-        ),
+        )
+        .with_flags(&[
+            ("-p", "create parent directories"),
+            ("-m", "set the mode"),
+            ("-v", "report each directory"),
+            ("--parents", "create parent directories"),
+            ("--mode", "set the mode"),
+            ("--verbose", "report each directory"),
+        ]),
         BuiltinCommand::new(
             "cp",
             "📁 File Operations",
             "Copy files",
             "cp [OPTIONS] SOURCE... DEST",
-        ),
+        )
+        .with_flags(&[
+            ("-r", "copy directories recursively"),
+            ("-p", "preserve attributes"),
+            ("-v", "report each file"),
+            ("-i", "prompt before overwriting"),
+            ("-n", "never overwrite"),
+            ("-u", "overwrite only older files"),
+            ("-b", "back up overwritten files"),
+            ("--backup", "back up overwritten files"),
+            ("-j", "copy N files at a time"),
+            ("--reflink", "clone file blocks"),
+            ("--sparse", "keep or make holes"),
+            ("--progress", "show bytes, rate and time left"),
+            ("--verify", "verify the copy"),
+        ]),
         BuiltinCommand::new(
             "mv",
             "📁 File Operations",
             "Move/rename files",
             "mv [OPTIONS] SOURCE... DEST",
-        ),
+        )
+        .with_flags(&[
+            ("-f", "never prompt"),
+            ("-i", "prompt before overwriting"),
+            ("-n", "never overwrite"),
+            ("-u", "overwrite only older files"),
+            ("-v", "report each file"),
+            ("-b", "back up overwritten files"),
+            ("--backup", "back up overwritten files"),
+            ("--progress", "show progress across file systems"),
+        ]),
         BuiltinCommand::new(
             "rm",
             "📁 File Operations",
             "Remove files",
             "rm [OPTIONS] FILE...",
-        ),
+        )
+        .with_flags(&[
+            ("-r", "remove directories recursively"),
+            ("-f", "ignore missing files"),
+            ("-i", "prompt before each removal"),
+            ("-I", "prompt once before removing many"),
+            ("-d", "remove empty directories"),
+            ("-v", "report each file"),
+            ("--recursive", "remove directories recursively"),
+            ("--force", "ignore missing files"),
+            ("--dir", "remove empty directories"),
+            ("--verbose", "report each file"),
+            ("--trash", "move to the trash instead"),
+            ("--preserve-root", "never remove /"),
+        ]),
+        BuiltinCommand::new(
+            "trash",
+            "📁 File Operations",
+            "Move files to the trash, list, restore or empty it",
+            "trash [put] [-fv] FILE... | trash list | trash restore ITEM... | trash empty",
+        )
+        .with_flags(&[
+            ("-f", "ignore missing files"),
+            ("-v", "report each file"),
+        ]),
         BuiltinCommand::new(
             "chmod",
             "📁 File Operations",
@@ -368,80 +596,522 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "📁 File Operations",
             "Create links",
             "ln [OPTIONS] TARGET [LINK_NAME]",
-        ),
+        )
+        .with_flags(&[
+            ("-s", "make symbolic links"),
+            ("-f", "replace existing files"),
+            ("-v", "report each link"),
+            ("--symbolic", "make symbolic links"),
+            ("--force", "replace existing files"),
+        ]),
         BuiltinCommand::new(
             "find",
             "📁 File Operations",
             "Find files",
-            "find [PATH...] [EXPRESSION]",
-        ),
+            "find [-H | -L | -P] [PATH...] [EXPRESSION]",
+        )
+        .with_flags(&[
+            ("-name", "match the base name against a pattern"),
+            ("-type", "match the file type (f, d, l, ...)"),
+            ("-size", "match the size, like +10M"),
+            ("-mtime", "match the age in days"),
+            ("-exec", "run a command on each file, or many with '+'"),
+            ("-print0", "print paths ended by NUL"),
+            ("-maxdepth", "descend at most this many levels"),
+            ("-L", "follow symbolic links"),
+        ]),
         BuiltinCommand::new(
             "du",
             "📁 File Operations",
             "Disk usage",
-            "du [OPTIONS] [PATH...]",
-        ),
+            "du [-abchksx] [-d DEPTH] [-t SIZE] [--apparent-size] [--si] [FILE...]",
+        )
+        .with_flags(&[
+            ("-a", "list files as well as directories"),
+            ("-s", "only the total of each FILE"),
+            ("-d", "list at most this many levels below"),
+            ("-t", "leave out entries below the size, or above it if negative"),
+            ("-x", "stay on one file system"),
+            ("-h", "sizes in powers of 1024"),
+            ("--apparent-size", "lengths rather than space on disk"),
+            ("-c", "add a grand total"),
+        ]),
         BuiltinCommand::new(
             "df",
             "📁 File Operations",
             "Disk free space",
-            "df [OPTIONS] [FILESYSTEM...]",
-        ),
+            "df [-ahHiTk] [-t TYPE] [-x TYPE] [--output[=FIELD,...]] [FILE...]",
+        )
+        .with_flags(&[
+            ("-a", "include pseudo, duplicate and empty file systems"),
+            ("-h", "sizes in powers of 1024"),
+            ("-H", "sizes in powers of 1000"),
+            ("-i", "inode counts instead of blocks"),
+            ("-T", "print the file system type"),
+            ("-t", "only file systems of this type"),
+            ("-x", "leave out file systems of this type"),
+            ("--output", "the columns to show, by name"),
+        ]),
         BuiltinCommand::new(
             "stat",
             "📁 File Operations",
             "File information",
             "stat [OPTIONS] FILE...",
         ),
+        BuiltinCommand::new(
+            "basename",
+            "📁 File Operations",
+            "Strip directories and a suffix from names",
+            "basename NAME [SUFFIX] | basename -a [-s SUFFIX] [-z] NAME...",
+        )
+        .with_flags(&[
+            ("-a", "treat every operand as a name"),
+            ("-s", "remove a trailing suffix"),
+            ("-z", "end each line with NUL"),
+        ]),
+        BuiltinCommand::new(
+            "dirname",
+            "📁 File Operations",
+            "Strip the last component from names",
+            "dirname [-z] NAME...",
+        )
+        .with_flags(&[("-z", "end each line with NUL")]),
+        BuiltinCommand::new(
+            "realpath",
+            "📁 File Operations",
+            "Print resolved absolute paths",
+            "realpath [-e|-m] [-s] [-qz] [--relative-to=DIR] [--relative-base=DIR] FILE...",
+        )
+        .with_flags(&[
+            ("-e", "require every component to exist"),
+            ("-m", "allow missing components"),
+            ("-s", "do not follow symbolic links"),
+            ("-q", "suppress error messages"),
+            ("-z", "end each path with NUL"),
+            ("--relative-to", "print paths relative to a directory"),
+            ("--relative-base", "print paths under a directory relative to it"),
+        ]),
+        BuiltinCommand::new(
+            "readlink",
+            "📁 File Operations",
+            "Print where symbolic links point",
+            "readlink [-f|-e|-m] [-n] [-q|-s|-v] [-z] FILE...",
+        )
+        .with_flags(&[
+            ("-f", "follow every link to an absolute path"),
+            ("-e", "like -f, requiring every component to exist"),
+            ("-m", "like -f, allowing missing components"),
+            ("-n", "do not print the trailing newline"),
+            ("-v", "report errors"),
+            ("-z", "end each path with NUL"),
+        ]),
+        BuiltinCommand::new(
+            "mktemp",
+            "📁 File Operations",
+            "Create temporary files and directories",
+            "mktemp [-d] [-u] [-q] [-p DIR|--tmpdir[=DIR]] [-t] [--suffix=SUFF] [TEMPLATE]",
+        )
+        .with_flags(&[
+            ("-d", "create a directory"),
+            ("-u", "only print a free name"),
+            ("-q", "suppress error messages"),
+            ("-p", "create under a directory"),
+            ("-t", "create a bare name in the temporary directory"),
+            ("--suffix", "append a suffix to the name"),
+        ]),
+        BuiltinCommand::new(
+            "file",
+            "📁 File Operations",
+            "Determine file types",
+            "file [-bhiL] [--mime-type] [--mime-encoding] [-F SEP] [-f NAMEFILE] FILE...",
+        )
+        .with_flags(&[
+            ("-b", "do not print file names"),
+            ("-i", "print MIME type and character set"),
+            ("-L", "follow symbolic links"),
+            ("-h", "describe symbolic links themselves"),
+            ("-F", "separator after file names"),
+            ("-f", "read file names from a file"),
+            ("--mime-type", "print only the MIME type"),
+            ("--mime-encoding", "print only the character set"),
+        ]),
+        BuiltinCommand::new(
+            "getfattr",
+            "📁 File Operations",
+            "Print extended attributes",
+            "getfattr [-hd] [-n NAME] [-m PATTERN] [-e ENCODING] FILE...",
+        )
+        .with_flags(&[
+            ("-d", "print the values too"),
+            ("-n", "print the named attribute"),
+            ("-m", "names matching a regular expression"),
+            ("-e", "encode values as text, hex or base64"),
+            ("-h", "do not follow symbolic links"),
+            ("--only-values", "print the bare values"),
+        ]),
+        BuiltinCommand::new(
+            "setfattr",
+            "📁 File Operations",
+            "Set or remove extended attributes",
+            "setfattr [-h] -n NAME [-v VALUE] | -x NAME FILE...",
+        )
+        .with_flags(&[
+            ("-n", "the attribute to set"),
+            ("-v", "its value"),
+            ("-x", "remove the attribute"),
+            ("-h", "do not follow symbolic links"),
+        ]),
+        BuiltinCommand::new(
+            "dd",
+            "📁 File Operations",
+            "Copy and convert data in blocks",
+            "dd [if=FILE] [of=FILE] [bs=BYTES] [count=N] [skip=N] [seek=N] [conv=CONVS] [status=LEVEL]",
+        )
+        .with_flags(&[
+            ("if=", "read from a file instead of standard input"),
+            ("of=", "write to a file instead of standard output"),
+            ("bs=", "read and write this many bytes at a time"),
+            ("count=", "copy only this many input blocks"),
+            ("skip=", "skip input blocks first"),
+            ("seek=", "skip output blocks first"),
+            ("conv=", "notrunc, fsync, fdatasync, sync, excl, nocreat"),
+            ("status=", "none, noxfer or progress"),
+        ]),
+        BuiltinCommand::new(
+            "tree",
+            "📁 File Operations",
+            "List directory contents as a tree",
+            "tree [-adhnpsC] [-L LEVEL] [-P PATTERN] [-I PATTERN] [--gitignore] [DIR]...",
+        )
+        .with_flags(&[
+            ("-a", "list hidden entries too"),
+            ("-d", "list directories only"),
+            ("-L", "descend at most this many levels"),
+            ("-P", "list only files matching a pattern"),
+            ("-I", "leave out entries matching a pattern"),
+            ("-p", "show permissions"),
+            ("-s", "show sizes"),
+            ("-h", "show sizes with units"),
+            ("-C", "always color names"),
+            ("-n", "never color names"),
+            ("--gitignore", "leave out what .gitignore files ignore"),
+            ("--dirsfirst", "list directories before files"),
+            ("--icons", "show file icons"),
+            ("--noreport", "leave out the directory and file counts"),
+        ]),
+        BuiltinCommand::new(
+            "sync",
+            "📁 File Operations",
+            "Copy directory trees incrementally",
+            "sync [-acnuv] [--delete] [--size-only] [--exclude PATTERN] [--include PATTERN] [-e COMMAND] SRC... DEST",
+        )
+        .with_flags(&[
+            ("-n", "only report what would change"),
+            ("-v", "list what is copied and deleted"),
+            ("-c", "compare files by checksum"),
+            ("-u", "leave alone files newer in the destination"),
+            ("--size-only", "compare files by size only"),
+            ("--delete", "delete files missing from the source"),
+            ("--exclude", "leave out what a pattern matches"),
+            ("--include", "keep what a pattern matches"),
+            ("-e", "command that reaches other hosts"),
+        ]),
+        BuiltinCommand::new(
+            "rcp",
+            "📁 File Operations",
+            "Copy directory trees incrementally",
+            "rcp [-acnuv] [--delete] [--size-only] [--exclude PATTERN] [--include PATTERN] [-e COMMAND] SRC... DEST",
+        ),
         // Text Processing 📝
+        BuiltinCommand::new(
+            "awk",
+            "📝 Text Processing",
+            "Pattern scanning and processing language",
+            "awk [-F FS] [-v VAR=VALUE] {PROGRAM | -f PROGFILE} [FILE...]",
+        )
+        .with_flags(&[
+            ("-F", "field separator"),
+            ("-v", "assign a variable before BEGIN"),
+            ("-f", "read the program from a file"),
+        ]),
         BuiltinCommand::new(
             "cat",
             "📝 Text Processing",
             "Display file contents",
             "cat [OPTIONS] [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-n", "number all lines"),
+            ("-b", "number non-blank lines"),
+            ("-s", "squeeze blank lines"),
+            ("-E", "show line ends"),
+            ("-T", "show tabs"),
+            ("-A", "show all non-printing chars"),
+            ("--number", "number all lines"),
+            ("--number-nonblank", "number non-blank lines"),
+            ("--squeeze-blank", "squeeze blank lines"),
+            ("--show-all", "show all non-printing chars"),
+            ("-z", "expand compressed standard input too"),
+            ("--no-decompress", "never expand compressed input"),
+        ]),
         BuiltinCommand::new(
             "echo",
             "📝 Text Processing",
             "Output text",
             "echo [OPTIONS] [STRING...]",
-        ),
+        )
+        .with_flags(&[
+            ("-n", "no trailing newline"),
+            ("-e", "interpret backslash escapes"),
+            ("-E", "do not interpret escapes"),
+        ]),
         BuiltinCommand::new(
             "grep",
             "📝 Text Processing",
             "Search text patterns",
             "grep [OPTIONS] PATTERN [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-E", "extended regular expressions"),
+            ("-F", "fixed strings"),
+            ("-P", "Perl-style regular expressions"),
+            ("-i", "ignore case"),
+            ("-v", "select non-matching lines"),
+            ("-w", "match whole words"),
+            ("-n", "show line numbers"),
+            ("-c", "count matching lines"),
+            ("-l", "list matching files"),
+            ("-o", "show only the matches"),
+            ("-r", "search directories recursively"),
+            ("-A", "lines of trailing context"),
+            ("-B", "lines of leading context"),
+            ("-C", "lines of context"),
+            ("--color", "highlight matches"),
+            ("--decompress", "expand compressed standard input too"),
+            ("--no-decompress", "search compressed files as they are"),
+        ]),
         BuiltinCommand::new(
             "head",
             "📝 Text Processing",
             "Show file beginning",
             "head [OPTIONS] [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-n", "number of lines"),
+            ("-c", "number of bytes"),
+            ("-q", "never print headers"),
+            ("-v", "always print headers"),
+            ("--lines", "number of lines"),
+            ("--bytes", "number of bytes"),
+            ("-z", "expand compressed standard input too"),
+        ]),
         BuiltinCommand::new(
             "tail",
             "📝 Text Processing",
             "Show file end",
             "tail [OPTIONS] [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-n", "number of lines"),
+            ("-c", "number of bytes"),
+            ("-f", "follow appended data"),
+            ("-q", "never print headers"),
+            ("-v", "always print headers"),
+            ("--lines", "number of lines"),
+            ("--bytes", "number of bytes"),
+            ("--follow", "follow appended data"),
+            ("-z", "expand compressed standard input too"),
+        ]),
         BuiltinCommand::new(
             "cut",
             "📝 Text Processing",
             "Extract columns",
             "cut [OPTIONS] [FILE...]",
         ),
+        BuiltinCommand::new(
+            "diff",
+            "📝 Text Processing",
+            "Compare files line by line",
+            "diff [OPTIONS] FILE1 FILE2",
+        )
+        .with_flags(&[
+            ("-u", "unified format"),
+            ("-U", "unified format with NUM context lines"),
+            ("-c", "context format"),
+            ("-C", "context format with NUM context lines"),
+            ("-q", "only report whether files differ"),
+            ("-r", "compare directories recursively"),
+            ("-N", "treat missing files as empty"),
+            ("-x", "skip entries matching a pattern"),
+            ("-i", "ignore case"),
+            ("-b", "ignore changes in white space"),
+            ("-w", "ignore all white space"),
+            ("-a", "compare binary files as text"),
+            ("--label", "name a file in headers"),
+            ("--color", "color the output"),
+        ]),
+        BuiltinCommand::new(
+            "tee",
+            "📝 Text Processing",
+            "Copy standard input to files and standard output",
+            "tee [-a] [FILE...]",
+        )
+        .with_flags(&[
+            ("-a", "append to the files"),
+            ("-i", "ignore interrupts"),
+            ("-p", "diagnose write errors"),
+        ]),
+        BuiltinCommand::new(
+            "split",
+            "📝 Text Processing",
+            "Split a file into pieces",
+            "split [OPTIONS] [FILE [PREFIX]]",
+        )
+        .with_flags(&[
+            ("-l", "lines per piece"),
+            ("-b", "bytes per piece"),
+            ("-n", "number of pieces"),
+            ("-a", "suffix length"),
+            ("-d", "numeric suffixes"),
+            ("--additional-suffix", "suffix appended to names"),
+            ("--verbose", "report each file created"),
+        ]),
+        BuiltinCommand::new(
+            "paste",
+            "📝 Text Processing",
+            "Merge lines of files",
+            "paste [-s] [-d LIST] [FILE...]",
+        )
+        .with_flags(&[
+            ("-d", "delimiters to use in turn"),
+            ("-s", "paste each file on one line"),
+        ]),
+        BuiltinCommand::new(
+            "join",
+            "📝 Text Processing",
+            "Join lines of two files on a common field",
+            "join [OPTIONS] FILE1 FILE2",
+        )
+        .with_flags(&[
+            ("-t", "field separator"),
+            ("-1", "join field of FILE1"),
+            ("-2", "join field of FILE2"),
+            ("-j", "join field of both files"),
+            ("-a", "also print unpairable lines of a file"),
+            ("-v", "print only unpairable lines of a file"),
+            ("-e", "replacement for missing fields"),
+            ("-o", "output format"),
+            ("-i", "ignore case in join fields"),
+            ("--header", "join the first lines as headers"),
+            ("--nocheck-order", "do not check the input order"),
+        ]),
+        BuiltinCommand::new(
+            "comm",
+            "📝 Text Processing",
+            "Compare two sorted files line by line",
+            "comm [OPTIONS] FILE1 FILE2",
+        )
+        .with_flags(&[
+            ("-1", "suppress lines only in FILE1"),
+            ("-2", "suppress lines only in FILE2"),
+            ("-3", "suppress lines in both files"),
+            ("--output-delimiter", "column separator"),
+            ("--total", "print a summary line"),
+            ("--nocheck-order", "do not check the input order"),
+        ]),
+        BuiltinCommand::new(
+            "nl",
+            "📝 Text Processing",
+            "Number lines of files",
+            "nl [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-b", "body numbering style"),
+            ("-h", "header numbering style"),
+            ("-f", "footer numbering style"),
+            ("-v", "first line number"),
+            ("-i", "line number increment"),
+            ("-w", "line number width"),
+            ("-s", "separator after numbers"),
+            ("-n", "number format: ln, rn or rz"),
+            ("-l", "empty lines counted as one"),
+            ("-d", "section delimiter characters"),
+            ("-p", "do not restart numbering at pages"),
+        ]),
+        BuiltinCommand::new(
+            "tac",
+            "📝 Text Processing",
+            "Write files with their lines in reverse order",
+            "tac [-b] [-r] [-s SEP] [FILE...]",
+        )
+        .with_flags(&[
+            ("-b", "attach the separator before records"),
+            ("-r", "separator is a regular expression"),
+            ("-s", "record separator"),
+        ]),
+        BuiltinCommand::new(
+            "rev",
+            "📝 Text Processing",
+            "Reverse the characters of each line",
+            "rev [FILE...]",
+        ),
+        BuiltinCommand::new(
+            "less",
+            "📝 Text Processing",
+            "Page through text with scrolling and search",
+            "less [-EFiNRrS] [FILE]...",
+        )
+        .with_flags(&[
+            ("-E", "quit at the end"),
+            ("-F", "print text that fits on the screen"),
+            ("-i", "search ignoring case"),
+            ("-N", "number the lines"),
+            ("-R", "show ANSI colors"),
+            ("-r", "show ANSI colors"),
+            ("-S", "chop long lines"),
+        ]),
+        BuiltinCommand::new(
+            "more",
+            "📝 Text Processing",
+            "Page through text, quitting at the end",
+            "more [-EFiNRrS] [FILE]...",
+        ),
         BuiltinCommand::new(
             "tr",
             "📝 Text Processing",
             "Translate characters",
             "tr [OPTIONS] SET1 [SET2]",
         ),
+        BuiltinCommand::new(
+            "sed",
+            "📝 Text Processing",
+            "Stream editor",
+            "sed [OPTIONS] SCRIPT [FILE...]",
+        )
+        .with_flags(&[
+            ("-n", "print only what the script prints"),
+            ("-e", "add a script expression"),
+            ("-f", "add a script file"),
+            ("-E", "extended regular expressions"),
+            ("-i", "edit files in place"),
+            ("-s", "treat files separately"),
+        ]),
         BuiltinCommand::new(
             "sort",
             "📝 Text Processing",
             "Sort lines",
             "sort [OPTIONS] [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-n", "compare numerically"),
+            ("-r", "reverse the order"),
+            ("-u", "drop duplicate lines"),
+            ("-f", "ignore case"),
+            ("--numeric-sort", "compare numerically"),
+            ("--reverse", "reverse the order"),
+            ("--unique", "drop duplicate lines"),
+            ("--ignore-case", "ignore case"),
+        ]),
         BuiltinCommand::new(
             "uniq",
             "📝 Text Processing",
@@ -453,26 +1123,94 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "📝 Text Processing",
             "Count lines/words",
             "wc [OPTIONS] [FILE...]",
-        ),
+        )
+        .with_flags(&[
+            ("-l", "count lines"),
+            ("-w", "count words"),
+            ("-c", "count bytes"),
+            ("--lines", "count lines"),
+            ("--words", "count words"),
+            ("--bytes", "count bytes"),
+            ("--chars", "count chars"),
+            ("--max-line-length", "longest line length"),
+        ]),
         // System Monitoring 📊
         BuiltinCommand::new(
             "ps",
             "📊 System Monitoring",
             "Process status",
-            "ps [OPTIONS]",
-        ),
+            "ps [-eAf] [-o FIELDS] [-p PIDS] [-u USERS] [--sort KEYS]",
+        )
+        .with_flags(&[
+            ("-e", "list every process"),
+            ("-f", "full format"),
+            ("-o", "columns to show"),
+            ("-p", "select by process ID"),
+            ("-u", "select by user"),
+            ("-C", "select by name"),
+            ("--ppid", "select by parent"),
+            ("--sort", "order by fields"),
+            ("--no-headers", "omit the header line"),
+        ]),
         BuiltinCommand::new(
             "kill",
             "📊 System Monitoring",
             "Terminate processes",
             "kill [SIGNAL] PID...",
-        ),
+        )
+        .with_flags(&[
+            ("-s", "signal to send"),
+            ("-l", "list signal names"),
+            ("-L", "list signals as a table"),
+            ("--signal", "signal to send"),
+            ("--list", "list signal names"),
+        ]),
+        BuiltinCommand::new(
+            "pgrep",
+            "📊 System Monitoring",
+            "Find processes by name",
+            "pgrep [-acfilnovx] [-d DELIM] [-u USERS] [-P PPIDS] [PATTERN]",
+        )
+        .with_flags(&[
+            ("-f", "match whole command lines"),
+            ("-l", "print names too"),
+            ("-a", "print command lines too"),
+            ("-u", "only these users' processes"),
+            ("-P", "only these parents' children"),
+            ("-x", "match exactly"),
+            ("-n", "only the newest"),
+            ("-o", "only the oldest"),
+        ]),
+        BuiltinCommand::new(
+            "pkill",
+            "📊 System Monitoring",
+            "Signal processes by name",
+            "pkill [-SIGNAL] [-cefinovx] [-u USERS] [-P PPIDS] [PATTERN]",
+        )
+        .with_flags(&[
+            ("--signal", "signal to send"),
+            ("-f", "match whole command lines"),
+            ("-e", "report each process signalled"),
+            ("-u", "only these users' processes"),
+            ("-P", "only these parents' children"),
+            ("-x", "match exactly"),
+        ]),
         BuiltinCommand::new(
             "top",
             "📊 System Monitoring",
             "Process monitor",
-            "top [OPTIONS]",
-        ),
+            "top [-bci] [-n COUNT] [-d SECS] [-p PIDS] [-u USER] [-o FIELD]",
+        )
+        .with_flags(&[
+            ("-b", "batch mode"),
+            ("-n", "number of frames"),
+            ("-d", "seconds between frames"),
+            ("-p", "show only these processes"),
+            ("-u", "show only this user's processes"),
+            ("-o", "field to order by"),
+            ("-c", "show command lines"),
+            ("-i", "hide idle processes"),
+        ]),
         BuiltinCommand::new(
             "jobs",
             "📊 System Monitoring",
@@ -498,26 +1236,188 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "free [OPTIONS]",
         ),
         BuiltinCommand::new("uptime", "📊 System Monitoring", "System uptime", "uptime"),
+        BuiltinCommand::new(
+            "watch",
+            "📊 System Monitoring",
+            "Re-run a command periodically",
+            "watch [-n SECS] [-d] [-t] [-g] [-x] COMMAND [ARG]...",
+        )
+        .with_flags(&[
+            ("-n", "seconds between runs"),
+            ("-d", "highlight changes"),
+            ("-t", "hide the header"),
+            ("-g", "exit when the output changes"),
+            ("-x", "run without a shell"),
+            ("--interval", "seconds between runs"),
+            ("--differences", "highlight changes"),
+            ("--no-title", "hide the header"),
+            ("--chgexit", "exit when the output changes"),
+            ("--exec", "run without a shell"),
+        ]),
         BuiltinCommand::new("whoami", "📊 System Monitoring", "Current user", "whoami"),
         // Network Tools 🌐
         BuiltinCommand::new(
             "ping",
             "🌐 Network Tools",
             "Network ping",
-            "ping [OPTIONS] DESTINATION",
-        ),
+            "ping [-46q] [-c COUNT] [-i SECS] [-W SECS] [-t TTL] [-s SIZE] HOST",
+        )
+        .with_flags(&[
+            ("-4", "only IPv4"),
+            ("-6", "only IPv6"),
+            ("-c", "stop after COUNT requests"),
+            ("-i", "seconds between requests"),
+            ("-W", "seconds to wait for each reply"),
+            ("-t", "time to live of the requests"),
+            ("-s", "bytes of data in each request"),
+            ("-q", "only the summary"),
+        ]),
         BuiltinCommand::new(
             "curl",
             "🌐 Network Tools",
-            "HTTP client",
+            "Transfer data from and to HTTP servers",
             "curl [OPTIONS] URL",
-        ),
+        )
+        .with_flags(&[
+            ("-X", "the method to use"),
+            ("-H", "add or replace a header"),
+            ("-d", "send data, @FILE for a file's"),
+            ("-F", "add a multipart form field"),
+            ("--json", "send and pretty-print JSON"),
+            ("-u", "basic authentication"),
+            ("-L", "follow redirects"),
+            ("-x", "the proxy to use"),
+            ("--retry", "try again after transient failures"),
+            ("-m", "seconds to give each try"),
+            ("-i", "print the reply's headers"),
+            ("-o", "write to a file"),
+            ("-f", "fail on HTTP errors"),
+            ("-s", "print no errors or warnings"),
+            ("-v", "show the headers sent and received"),
+        ]),
         BuiltinCommand::new(
             "wget",
             "🌐 Network Tools",
-            "File downloader",
-            "wget [OPTIONS] URL",
-        ),
+            "Download files over HTTP",
+            "wget [OPTIONS] URL...",
+        )
+        .with_flags(&[
+            ("-O", "write everything to one file"),
+            ("-P", "save files under a directory"),
+            ("-c", "continue a partial download"),
+            ("-r", "follow links recursively"),
+            ("-l", "how deep to recurse"),
+            ("-np", "never climb above the start"),
+            ("--limit-rate", "bytes a second at most"),
+            ("--segments", "parallel Range requests per file"),
+            ("-t", "tries for each file"),
+            ("-q", "print nothing"),
+        ]),
+        BuiltinCommand::new(
+            "netstat",
+            "🌐 Network Tools",
+            "Open sockets and their processes",
+            "netstat [-atulpn46]",
+        )
+        .with_flags(&[
+            ("-a", "connected and listening sockets"),
+            ("-l", "only listening sockets"),
+            ("-t", "only TCP"),
+            ("-u", "only UDP"),
+            ("-p", "show the owning process"),
+            ("-4", "only IPv4"),
+            ("-6", "only IPv6"),
+        ]),
+        BuiltinCommand::new(
+            "dig",
+            "🌐 Network Tools",
+            "Query DNS name servers",
+            "dig [@SERVER] [-t TYPE] [-x ADDRESS] [+short] [+json] [NAME] [TYPE]",
+        )
+        .with_flags(&[
+            ("-p", "server port"),
+            ("-t", "record type"),
+            ("-x", "reverse lookup of an address"),
+            ("+short", "only the answer's data"),
+            ("+json", "the answer as JSON"),
+            ("+tcp", "query over TCP"),
+            ("+time", "seconds to wait for an answer"),
+            ("+tries", "times to send a UDP query"),
+        ]),
+        BuiltinCommand::new(
+            "traceroute",
+            "🌐 Network Tools",
+            "Routers on the way to a host",
+            "traceroute [-46InU] [-f FIRST] [-m MAX] [-q PROBES] [-w SECS] [-p PORT] HOST",
+        )
+        .with_flags(&[
+            ("-I", "probe with ICMP echo requests"),
+            ("-U", "probe with UDP datagrams"),
+            ("-n", "addresses without names"),
+            ("-f", "first time to live"),
+            ("-m", "largest time to live"),
+            ("-q", "probes per hop"),
+            ("-w", "seconds to wait for each probe"),
+            ("-p", "first UDP port"),
+        ]),
+        BuiltinCommand::new(
+            "tracert",
+            "🌐 Network Tools",
+            "Routers on the way to a host",
+            "tracert [-d] [-4 | -6] [-h MAX] [-w MSECS] HOST",
+        )
+        .with_flags(&[
+            ("-d", "addresses without names"),
+            ("-h", "largest number of hops"),
+            ("-w", "milliseconds to wait for each probe"),
+        ]),
+        BuiltinCommand::new(
+            "nc",
+            "🌐 Network Tools",
+            "Read and write TCP and UDP connections",
+            "nc [-lnuvz] [-w SECS] [-p PORT] [HOST] PORT[-PORT]...",
+        )
+        .with_flags(&[
+            ("-l", "listen for a client"),
+            ("-p", "port to listen on"),
+            ("-u", "use UDP"),
+            ("-z", "only check whether ports are open"),
+            ("-v", "report connections on standard error"),
+            ("-w", "seconds to wait for a connection or data"),
+            ("-n", "no name lookups"),
+        ]),
+        #[cfg(feature = "remote")]
+        BuiltinCommand::new(
+            "ssh",
+            "🌐 Network Tools",
+            "Log in to another host or run a command there",
+            "ssh [-nqTt] [-i FILE] [-l USER] [-o OPTION] [-p PORT] [USER@]HOST [COMMAND...]",
+        )
+        .with_flags(&[
+            ("-p", "port to connect to"),
+            ("-l", "user to log in as"),
+            ("-i", "private key file to try first"),
+            ("-o", "OpenSSH option as Key=Value"),
+            ("-t", "ask for a remote terminal"),
+            ("-T", "never ask for a remote terminal"),
+            ("-n", "read nothing from standard input"),
+            ("-q", "leave out warnings"),
+        ]),
+        #[cfg(feature = "remote")]
+        BuiltinCommand::new(
+            "scp",
+            "🌐 Network Tools",
+            "Copy files to and from other hosts",
+            "scp [-pqr] [-i FILE] [-o OPTION] [-P PORT] SOURCE... TARGET",
+        )
+        .with_flags(&[
+            ("-r", "copy directories recursively"),
+            ("-p", "keep times and permissions"),
+            ("-q", "leave out warnings"),
+            ("-P", "port to connect to"),
+            ("-i", "private key file to try first"),
+            ("-o", "OpenSSH option as Key=Value"),
+        ]),
         // Shell Utilities 🔧
         BuiltinCommand::new(
             "which",
@@ -525,6 +1425,22 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "Locate commands",
             "which COMMAND...",
         ),
+        BuiltinCommand::new(
+            "xargs",
+            "🔧 Shell Utilities",
+            "Build and run command lines from standard input",
+            "xargs [OPTIONS] [COMMAND [ARG...]]",
+        )
+        .with_flags(&[
+            ("-0", "items are separated by NUL"),
+            ("-d", "items are separated by a delimiter"),
+            ("-I", "replace a string with each input line"),
+            ("-n", "items per command line"),
+            ("-L", "input lines per command line"),
+            ("-P", "commands to run at a time"),
+            ("-r", "do not run on empty input"),
+            ("-t", "print commands before running them"),
+        ]),
         BuiltinCommand::new(
             "sleep",
             "🔧 Shell Utilities",
@@ -541,15 +1457,104 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "env",
             "🔧 Shell Utilities",
             "Environment variables",
-            "env [OPTIONS] [COMMAND [ARGS]]",
-        ),
+            "env [-0i] [-u NAME]... [-C DIR] [NAME=VALUE]... [COMMAND [ARG]...]",
+        )
+        .with_flags(&[
+            ("-i", "start with an empty environment"),
+            ("-u", "remove a variable from the environment"),
+            ("-C", "run the command in another directory"),
+            ("-0", "end each printed variable with NUL"),
+        ]),
         BuiltinCommand::new(
             "export",
             "🔧 Shell Utilities",
             "Export variables",
             "export [OPTIONS] [NAME[=VALUE]...]",
+        )
+        .with_flags(&[
+            ("-p", "list exported variables"),
+            ("-n", "remove the export attribute"),
+            ("-f", "export functions"),
+        ]),
+        BuiltinCommand::new("yes", "🔧 Shell Utilities", "Repeat output", "yes [STRING...]"),
+        BuiltinCommand::new(
+            "seq",
+            "🔧 Shell Utilities",
+            "Print a sequence of numbers",
+            "seq [-w] [-f FORMAT] [-s SEP] [FIRST [INCREMENT]] LAST",
+        )
+        .with_flags(&[
+            ("-f", "format each number with a printf conversion"),
+            ("-s", "separate numbers with a string"),
+            ("-w", "pad numbers with zeros to equal width"),
+        ]),
+        BuiltinCommand::new(
+            "shuf",
+            "🔧 Shell Utilities",
+            "Write a random permutation of the input lines",
+            "shuf [-erz] [-i LO-HI] [-n COUNT] [-o FILE] [--random-source=FILE] [FILE | ARG...]",
+        )
+        .with_flags(&[
+            ("-e", "shuffle the arguments"),
+            ("-i", "shuffle the numbers LO to HI"),
+            ("-n", "write at most COUNT lines"),
+            ("-o", "write to a file"),
+            ("-r", "pick lines with repeats"),
+            ("-z", "end lines with NUL"),
+            ("--random-source", "take randomness from a file"),
+        ]),
+        BuiltinCommand::new(
+            "factor",
+            "🔧 Shell Utilities",
+            "Print the prime factors of numbers",
+            "factor [-h] [NUMBER...]",
+        )
+        .with_flags(&[("-h", "print repeated factors as powers")]),
+        BuiltinCommand::new(
+            "test",
+            "🔧 Shell Utilities",
+            "Evaluate a conditional expression",
+            "test EXPRESSION",
+        )
+        .with_flags(&[
+            ("-e", "the file exists"),
+            ("-f", "the file is a regular file"),
+            ("-d", "the file is a directory"),
+            ("-n", "the string is not empty"),
+            ("-z", "the string is empty"),
+        ]),
+        BuiltinCommand::new(
+            "[",
+            "🔧 Shell Utilities",
+            "Evaluate a conditional expression",
+            "[ EXPRESSION ]",
         ),
-        BuiltinCommand::new("yes", "🔧 Shell Utilities", "Repeat output", "yes [STRING]"),
+        BuiltinCommand::new(
+            "ulimit",
+            "🔧 Shell Utilities",
+            "Report and set resource limits",
+            "ulimit [-SH] [-a | -cdefilmnqrstuvx [LIMIT]]",
+        )
+        .with_flags(&[
+            ("-S", "use the soft limit"),
+            ("-H", "use the hard limit"),
+            ("-a", "print every limit"),
+            ("-c", "core file size"),
+            ("-n", "open files"),
+            ("-s", "stack size"),
+            ("-t", "cpu time"),
+            ("-v", "virtual memory"),
+        ]),
+        BuiltinCommand::new(
+            "umask",
+            "🔧 Shell Utilities",
+            "Report and set the file creation mask",
+            "umask [-p] [-S] [MODE]",
+        )
+        .with_flags(&[
+            ("-S", "print the mask in symbolic form"),
+            ("-p", "print a command that restores the mask"),
+        ]),
         BuiltinCommand::new("true", "🔧 Shell Utilities", "Success command", "true"),
         BuiltinCommand::new(
             "uname",
@@ -562,7 +1567,8 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "🔧 Shell Utilities",
             "Remove variables",
             "unset [OPTIONS] [NAME...]",
-        ),
+        )
+        .with_flags(&[("-v", "unset variables"), ("-f", "unset functions")]),
         BuiltinCommand::new(
             "unalias",
             "🔧 Shell Utilities",
@@ -573,15 +1579,62 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
         BuiltinCommand::new(
             "tar",
             "📦 Archive & Compression",
-            "Archive files",
-            "tar [OPTIONS] [FILE...]",
-        ),
+            "Create, extract or list archives",
+            "tar {-c|-x|-t} [OPTIONS] [-f ARCHIVE] [MEMBER...]",
+        )
+        .with_flags(&[
+            ("-c", "create an archive"),
+            ("-x", "extract members"),
+            ("-t", "list members"),
+            ("-f", "archive file, - for stdin/stdout"),
+            ("-C", "change to directory first"),
+            ("-v", "list processed members"),
+            ("-z/-j/-J", "gzip, bzip2 or xz compression"),
+            ("--zstd", "zstd compression"),
+            ("-a", "pick compression from the suffix"),
+            ("-p", "keep permissions exactly"),
+            ("--exclude", "skip members matching a pattern"),
+        ]),
         BuiltinCommand::new(
             "gzip",
             "📦 Archive & Compression",
-            "GZIP compression",
-            "gzip [OPTIONS] [FILE...]",
-        ),
+            "Compress or expand files",
+            "gzip [-cdfklnNqrtv1-9] [-S SUFFIX] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "write to standard output"),
+            ("-d", "decompress"),
+            ("-f", "overwrite existing files"),
+            ("-k", "keep input files"),
+            ("-l", "list compressed sizes"),
+            ("-n", "do not save the name and time"),
+            ("-N", "restore the saved name and time"),
+            ("-r", "recurse into directories"),
+            ("-t", "test compressed files"),
+            ("-v", "report each file"),
+            ("-1..-9", "fast to best compression"),
+            ("-S", "suffix instead of .gz"),
+        ]),
+        BuiltinCommand::new(
+            "gunzip",
+            "📦 Archive & Compression",
+            "Expand gzip files",
+            "gunzip [-cfklnNqrtv] [-S SUFFIX] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "write to standard output"),
+            ("-f", "overwrite existing files"),
+            ("-k", "keep input files"),
+            ("-N", "restore the saved name and time"),
+            ("-t", "test compressed files"),
+        ]),
+        BuiltinCommand::new(
+            "zcat",
+            "📦 Archive & Compression",
+            "Print gzip files expanded",
+            "zcat [-fqv] [FILE...]",
+        )
+        .with_flags(&[("-f", "copy data that is not gzip as it is")]),
         BuiltinCommand::new(
             "bzip2",
             "📦 Archive & Compression",
@@ -634,6 +1687,97 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "cal [OPTIONS] [MONTH [YEAR]]",
         ),
         BuiltinCommand::new("cksum", "📄 Text Utilities", "Checksum", "cksum [FILE...]"),
+        BuiltinCommand::new(
+            "md5sum",
+            "📄 Text Utilities",
+            "Compute and check MD5 digests",
+            "md5sum [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "check digests listed in files"),
+            ("-b", "read in binary mode"),
+            ("-t", "read in text mode"),
+            ("-z", "end lines with NUL"),
+            ("-w", "warn about malformed lines"),
+            ("--tag", "BSD-style output"),
+            ("--quiet", "do not print OK lines"),
+            ("--status", "print nothing, report by exit status"),
+            ("--strict", "fail on malformed lines"),
+            ("--ignore-missing", "skip files that do not exist"),
+        ]),
+        BuiltinCommand::new(
+            "sha1sum",
+            "📄 Text Utilities",
+            "Compute and check SHA-1 digests",
+            "sha1sum [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "check digests listed in files"),
+            ("-b", "read in binary mode"),
+            ("-t", "read in text mode"),
+            ("-z", "end lines with NUL"),
+            ("-w", "warn about malformed lines"),
+            ("--tag", "BSD-style output"),
+            ("--quiet", "do not print OK lines"),
+            ("--status", "print nothing, report by exit status"),
+            ("--strict", "fail on malformed lines"),
+            ("--ignore-missing", "skip files that do not exist"),
+        ]),
+        BuiltinCommand::new(
+            "sha256sum",
+            "📄 Text Utilities",
+            "Compute and check SHA-256 digests",
+            "sha256sum [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "check digests listed in files"),
+            ("-b", "read in binary mode"),
+            ("-t", "read in text mode"),
+            ("-z", "end lines with NUL"),
+            ("-w", "warn about malformed lines"),
+            ("--tag", "BSD-style output"),
+            ("--quiet", "do not print OK lines"),
+            ("--status", "print nothing, report by exit status"),
+            ("--strict", "fail on malformed lines"),
+            ("--ignore-missing", "skip files that do not exist"),
+        ]),
+        BuiltinCommand::new(
+            "sha512sum",
+            "📄 Text Utilities",
+            "Compute and check SHA-512 digests",
+            "sha512sum [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "check digests listed in files"),
+            ("-b", "read in binary mode"),
+            ("-t", "read in text mode"),
+            ("-z", "end lines with NUL"),
+            ("-w", "warn about malformed lines"),
+            ("--tag", "BSD-style output"),
+            ("--quiet", "do not print OK lines"),
+            ("--status", "print nothing, report by exit status"),
+            ("--strict", "fail on malformed lines"),
+            ("--ignore-missing", "skip files that do not exist"),
+        ]),
+        BuiltinCommand::new(
+            "b2sum",
+            "📄 Text Utilities",
+            "Compute and check BLAKE2b digests",
+            "b2sum [OPTIONS] [FILE...]",
+        )
+        .with_flags(&[
+            ("-c", "check digests listed in files"),
+            ("-b", "read in binary mode"),
+            ("-t", "read in text mode"),
+            ("-z", "end lines with NUL"),
+            ("-w", "warn about malformed lines"),
+            ("--tag", "BSD-style output"),
+            ("--quiet", "do not print OK lines"),
+            ("--status", "print nothing, report by exit status"),
+            ("--strict", "fail on malformed lines"),
+            ("--ignore-missing", "skip files that do not exist"),
+            ("-l", "digest length in bits"),
+        ]),
         // System Control 🎛️
         BuiltinCommand::new(
             "exec",
@@ -648,6 +1792,54 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "Evaluate expressions",
             "eval [ARG...]",
         ),
+        BuiltinCommand::new(
+            "elevate",
+            "🎛️ System Control",
+            "Run a command with administrative rights",
+            "elevate [-b BACKEND] [-u USER] COMMAND [ARG]... | elevate -s",
+        )
+        .with_flags(&[
+            ("-b", "elevate through sudo, doas, pkexec or uac"),
+            ("-u", "run as another user"),
+            ("-s", "replace the shell with an elevated one"),
+        ]),
+        BuiltinCommand::new(
+            "runas",
+            "🎛️ System Control",
+            "Run a command with administrative rights",
+            "runas [-b BACKEND] [-u USER] COMMAND [ARG]... | runas -s",
+        ),
+        BuiltinCommand::new(
+            "sandbox",
+            "🎛️ System Control",
+            "Run a command under a sandbox profile",
+            "sandbox run [-p PROFILE] [OPTION]... [--] COMMAND [ARG]... | sandbox profiles | sandbox show PROFILE",
+        )
+        .with_flags(&[
+            ("-p", "start from a named profile"),
+            ("-n", "refuse network sockets"),
+            ("-r", "make the file system read-only"),
+            ("-w", "keep a path writable"),
+            ("-R", "hide everything but a path"),
+            ("-c", "limit CPU time"),
+            ("-m", "limit memory"),
+            ("-t", "kill the command after a timeout"),
+            ("-f", "limit open files"),
+            ("-u", "limit processes"),
+        ]),
+        #[cfg(feature = "plugins")]
+        BuiltinCommand::new(
+            "plugin",
+            "🎛️ System Control",
+            "Install and manage shell plugins",
+            "plugin install [-k KEY] SOURCE | plugin list | plugin enable NAME | plugin disable NAME | plugin update [-f] NAME [SOURCE] | plugin remove NAME | plugin dev [--stop] [PATH] | plugin permissions [--reset] [NAME]",
+        )
+        .with_flags(&[
+            ("-k", "pin a public key for the plugin's signature"),
+            ("-f", "update even to the same or an older version"),
+            ("-s", "stop reloading a plugin in development"),
+            ("-r", "forget the permissions granted to a plugin"),
+        ]),
         // File System Tools 🔧
         BuiltinCommand::new(
             "fsck",
@@ -661,6 +1853,25 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "Log statistics",
             "logstats [OPTIONS] [FILE]",
         ),
+        BuiltinCommand::new(
+            "mount",
+            "🔧 File System Tools",
+            "List or mount file systems",
+            "mount [-luv] [-t TYPE] [--json] [SOURCE TARGET]",
+        ),
+        BuiltinCommand::new(
+            "lsblk",
+            "🔧 File System Tools",
+            "List block devices",
+            "lsblk [-abdfl] [DEVICE...]",
+        )
+        .with_flags(&[
+            ("-a", "include empty devices"),
+            ("-b", "sizes in bytes"),
+            ("-d", "leave out partitions"),
+            ("-f", "file system type, label and UUID"),
+            ("-l", "list without the tree"),
+        ]),
         // Compression Tools 🗜️
         BuiltinCommand::new(
             "zstd",
@@ -675,6 +1886,46 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "unzstd [OPTIONS] [FILE]",
         ),
         // System Time Tools ⏰
+        #[cfg(feature = "scheduler")]
+        BuiltinCommand::new(
+            "schedule",
+            "⏰ System Time Tools",
+            "Run commands later, once or repeatedly",
+            "schedule [list | add [-Dn] --at|--in|--every|--cron WHEN COMMAND... | remove|enable|disable ID...]",
+        )
+        .with_flags(&[
+            ("--at", "run once at TIME"),
+            ("--in", "run once after DURATION"),
+            ("--every", "run every DURATION"),
+            ("--cron", "run whenever the cron expression matches"),
+            ("-n", "report successful runs too"),
+            ("-D", "show notices on the desktop as well"),
+        ]),
+        #[cfg(feature = "scheduler")]
+        BuiltinCommand::new(
+            "at",
+            "⏰ System Time Tools",
+            "Run a command once at a given time",
+            "at [-D] TIME [COMMAND...] | at -l | at -r ID...",
+        )
+        .with_flags(&[
+            ("-l", "list the pending jobs"),
+            ("-r", "remove jobs"),
+            ("-D", "show notices on the desktop as well"),
+        ]),
+        #[cfg(feature = "scheduler")]
+        BuiltinCommand::new(
+            "cron",
+            "⏰ System Time Tools",
+            "Run a command on a cron schedule",
+            "cron [-Dn] EXPR COMMAND... | cron -l | cron -r ID...",
+        )
+        .with_flags(&[
+            ("-l", "list the recurring jobs"),
+            ("-r", "remove jobs"),
+            ("-n", "report successful runs too"),
+            ("-D", "show notices on the desktop as well"),
+        ]),
         BuiltinCommand::new(
             "timedatectl",
             "⏰ System Time Tools",
@@ -698,8 +1949,151 @@ pub fn list_builtins() -> Vec<BuiltinCommand> {
             "printf",
             "📝 Variable Management Tools",
             "Formatted output",
-            "printf FORMAT [ARGS]",
-        ),
+            "printf [-v VAR] FORMAT [ARGUMENT...]",
+        )
+        .with_flags(&[("-v", "assign the output to a variable instead")]),
+    ]
+}
+
+/// Builtins that need the live `ShellContext` (trap table, variables, ...).
+/// They cannot go through [`execute_builtin`]; register them on the core
+/// executor instead, e.g. via `nxsh_core::Shell::register_builtin`.
+pub fn shell_builtins() -> Vec<std::sync::Arc<dyn nxsh_core::Builtin>> {
+    vec![
+        std::sync::Arc::new(trap::TrapCommand),
+        std::sync::Arc::new(set::SetCommand),
+        std::sync::Arc::new(shopt::ShoptCommand),
+        std::sync::Arc::new(shift::ShiftCommand),
+        std::sync::Arc::new(getopts::GetoptsCommand),
+        std::sync::Arc::new(read::ReadCommand),
+        std::sync::Arc::new(printf::PrintfCommand),
+        std::sync::Arc::new(test_builtin::TestCommand),
+        std::sync::Arc::new(test_builtin::BracketCommand),
+        std::sync::Arc::new(ulimit::UlimitCommand),
+        std::sync::Arc::new(umask::UmaskCommand),
+        std::sync::Arc::new(elevate::ElevateCommand),
+        std::sync::Arc::new(elevate::RunasCommand),
+        std::sync::Arc::new(sandbox::SandboxCommand),
+        #[cfg(feature = "plugins")]
+        std::sync::Arc::new(plugin::PluginManagerCommand),
+        std::sync::Arc::new(declare::DeclareCommand),
+        std::sync::Arc::new(local::LocalCommand),
+        std::sync::Arc::new(abbr::AbbrCommand),
+        std::sync::Arc::new(hook::HookCommand),
+        std::sync::Arc::new(cd::CdCommand),
+        std::sync::Arc::new(dirs::DirsCommand),
+        std::sync::Arc::new(dirs::PushdCommand),
+        std::sync::Arc::new(dirs::PopdCommand),
+        std::sync::Arc::new(grep::GrepCommand),
+        std::sync::Arc::new(sed::SedCommand),
+        std::sync::Arc::new(awk::AwkCommand),
+        std::sync::Arc::new(diff::DiffCommand),
+        std::sync::Arc::new(tee::TeeCommand),
+        std::sync::Arc::new(split::SplitCommand),
+        std::sync::Arc::new(paste::PasteCommand),
+        std::sync::Arc::new(join::JoinCommand),
+        std::sync::Arc::new(comm::CommCommand),
+        std::sync::Arc::new(nl::NlCommand),
+        std::sync::Arc::new(tac::TacCommand),
+        std::sync::Arc::new(rev::RevCommand),
+        std::sync::Arc::new(less::LessCommand),
+        std::sync::Arc::new(less::MoreCommand),
+        std::sync::Arc::new(find::FindCommand),
+        std::sync::Arc::new(xargs::XargsCommand),
+        std::sync::Arc::new(env::EnvCommand),
+        std::sync::Arc::new(tar::TarCommand),
+        std::sync::Arc::new(gzip::GzipCommand),
+        std::sync::Arc::new(gzip::GunzipCommand),
+        std::sync::Arc::new(gzip::ZcatCommand),
+        std::sync::Arc::new(hashsum::Md5sumCommand),
+        std::sync::Arc::new(hashsum::Sha1sumCommand),
+        std::sync::Arc::new(hashsum::Sha256sumCommand),
+        std::sync::Arc::new(hashsum::Sha512sumCommand),
+        std::sync::Arc::new(hashsum::B2sumCommand),
+        std::sync::Arc::new(seq::SeqCommand),
+        std::sync::Arc::new(shuf::ShufCommand),
+        std::sync::Arc::new(factor::FactorCommand),
+        std::sync::Arc::new(yes::YesCommand),
+        std::sync::Arc::new(basename::BasenameCommand),
+        std::sync::Arc::new(dirname::DirnameCommand),
+        std::sync::Arc::new(realpath::RealpathCommand),
+        std::sync::Arc::new(readlink::ReadlinkCommand),
+        std::sync::Arc::new(mktemp::MktempCommand),
+        std::sync::Arc::new(file::FileCommand),
+        std::sync::Arc::new(xattr::GetfattrCommand),
+        std::sync::Arc::new(xattr::SetfattrCommand),
+        std::sync::Arc::new(df::DfCommand),
+        std::sync::Arc::new(du::DuCommand),
+        std::sync::Arc::new(cp::CpCommand),
+        std::sync::Arc::new(mv::MvBuiltin),
+        std::sync::Arc::new(rm::RmCommand),
+        std::sync::Arc::new(trash::TrashCommand),
+        std::sync::Arc::new(mount::MountCommand),
+        std::sync::Arc::new(lsblk::LsblkCommand),
+        std::sync::Arc::new(dd::DdCommand),
+        std::sync::Arc::new(tree::TreeCommand),
+        std::sync::Arc::new(sync_cmd::SyncCommand),
+        std::sync::Arc::new(sync_cmd::RcpCommand),
+        std::sync::Arc::new(watch::WatchCommand),
+        std::sync::Arc::new(ps::PsCommand),
+        std::sync::Arc::new(top::TopCommand),
+        std::sync::Arc::new(pgrep::PgrepCommand),
+        std::sync::Arc::new(pgrep::PkillCommand),
+        std::sync::Arc::new(netstat::NetstatCommand),
+        std::sync::Arc::new(dig::DigCommand),
+        std::sync::Arc::new(ping::PingCommand),
+        std::sync::Arc::new(traceroute::TracerouteCommand),
+        std::sync::Arc::new(traceroute::TracertCommand),
+        std::sync::Arc::new(nc::NcCommand),
+        std::sync::Arc::new(curl::CurlCommand),
+        std::sync::Arc::new(wget::WgetCommand),
+        #[cfg(feature = "remote")]
+        std::sync::Arc::new(ssh::SshCommand),
+        #[cfg(feature = "remote")]
+        std::sync::Arc::new(scp::ScpCommand),
+        #[cfg(feature = "scheduler")]
+        std::sync::Arc::new(schedule::ScheduleCommand),
+        #[cfg(feature = "scheduler")]
+        std::sync::Arc::new(schedule::AtCommand),
+        #[cfg(feature = "scheduler")]
+        std::sync::Arc::new(schedule::CronCommand),
+    ]
+}
+
+/// Commands that pass typed tables along a pipeline when every stage is one
+/// of them, like `ls | where size -gt 1000 | sort-by size`
+pub fn structured_builtins() -> Vec<std::sync::Arc<dyn nxsh_core::StructuredBuiltin>> {
+    vec![
+        std::sync::Arc::new(structured::LsTable),
+        std::sync::Arc::new(structured::PsTable),
+        std::sync::Arc::new(structured::NetstatTable),
+        std::sync::Arc::new(structured::DigTable),
+        std::sync::Arc::new(structured::CurlReply),
+        std::sync::Arc::new(structured::DfTable),
+        std::sync::Arc::new(structured::MountTable),
+        std::sync::Arc::new(structured::LsblkTable),
+        std::sync::Arc::new(structured::DuTable),
+        std::sync::Arc::new(structured::TrashTable),
+        std::sync::Arc::new(structured::StatTable),
+        std::sync::Arc::new(structured::EnvTable),
+        std::sync::Arc::new(structured::WhereFilter),
+        std::sync::Arc::new(structured::SelectColumns),
+        std::sync::Arc::new(structured::SortBy),
+        std::sync::Arc::new(structured::GroupBy),
+        std::sync::Arc::new(structured::Get),
+        std::sync::Arc::new(structured::Update),
+        std::sync::Arc::new(structured::Flatten),
+        std::sync::Arc::new(structured::First),
+        std::sync::Arc::new(structured::Last),
+        std::sync::Arc::new(structured::Length),
+        std::sync::Arc::new(conversions::FromJson),
+        std::sync::Arc::new(conversions::ToJson),
+        std::sync::Arc::new(conversions::FromYaml),
+        std::sync::Arc::new(conversions::ToYaml),
+        std::sync::Arc::new(conversions::FromToml),
+        std::sync::Arc::new(conversions::ToToml),
+        std::sync::Arc::new(conversions::FromCsv),
+        std::sync::Arc::new(conversions::ToCsv),
     ]
 }
 
@@ -711,73 +2105,6 @@ pub use execute_builtin as export_builtin;
 
 // Export command re-export for compatibility
 pub use crate::export_builtin::export_cli;
-pub mod tar {
-    //! Tar archive functionality with proper feature gate support
-    use crate::common::{BuiltinContext, BuiltinError, BuiltinResult};
-    use anyhow::{anyhow, Result};
-
-    pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-        #[cfg(feature = "compression-tar")]
-        {
-            // Full tar implementation would go here in compression builds
-            Err(BuiltinError::NotImplemented(
-                "tar: Full implementation requires compression-tar feature".to_string(),
-            ))
-        }
-        #[cfg(not(feature = "compression-tar"))]
-        {
-            // Minimal builds provide helpful error
-            if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
-                println!("tar: Minimal build - external tar command required");
-                println!("Usage: Use system tar command for archive operations");
-                println!("Consider using zip/bzip2/xz commands for compression needs");
-                Ok(0)
-            } else {
-                Err(BuiltinError::NotImplemented("tar: Not available in minimal builds. Use system tar or enable compression feature.".to_string()))
-            }
-        }
-    }
-
-    pub fn tar_cli(args: &[String]) -> Result<(), anyhow::Error> {
-        let context = BuiltinContext::new();
-        execute(args, &context).map_err(|e| anyhow!(e.to_string()))?;
-        Ok(())
-    }
-}
-
-/// Grep functionality with minimal build support
-/// Text search functionality with feature-aware implementation
-pub mod grep {
-    use crate::common::{BuiltinContext, BuiltinError, BuiltinResult};
-    use anyhow::Result;
-
-    pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-        #[cfg(any(feature = "minimal", feature = "default"))]
-        {
-            // Full grep implementation would go here
-            Err(BuiltinError::NotImplemented(
-                "grep: Full implementation requires advanced text processing".to_string(),
-            ))
-        }
-        #[cfg(not(any(feature = "minimal", feature = "default")))]
-        {
-            if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
-                println!("grep: Minimal build - external grep command recommended");
-                println!("Usage: Use system grep command for pattern matching");
-                println!("Alternative: Use 'cat file | findstr pattern' on Windows");
-                Ok(0)
-            } else {
-                Err(BuiltinError::NotImplemented("grep: Not available in minimal builds. Use system grep or enable text-processing feature.".to_string()))
-            }
-        }
-    }
-
-    pub fn grep_cli(args: &[String]) -> Result<(), anyhow::Error> {
-        let context = BuiltinContext::new();
-        execute(args, &context)?;
-        Ok(())
-    }
-}
 
 /// Extended grep functionality (egrep)
 /// Extended regular expression grep with super-min build handling
@@ -849,6 +2176,7 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "cp" => cp_execute(args, &context).map_err(|e| e.to_string()),
         "mv" => mv_execute(args, &context).map_err(|e| e.to_string()),
         "rm" => rm_execute(args, &context).map_err(|e| e.to_string()),
+        "trash" => trash::execute(args, &context).map_err(|e| e.to_string()),
         "chmod" => chmod_execute(args, &context).map_err(|e| e.to_string()),
         "chown" => chown_execute(args, &context).map_err(|e| e.to_string()),
         "chgrp" => chgrp_execute(args, &context).map_err(|e| e.to_string()),
@@ -856,6 +2184,18 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "du" => du_execute(args, &context).map_err(|e| e.to_string()),
         "df" => df_execute(args, &context).map_err(|e| e.to_string()),
         "stat" => stat_execute(args, &context).map_err(|e| e.to_string()),
+        "basename" => basename::execute(args, &context).map_err(|e| e.to_string()),
+        "dirname" => dirname::execute(args, &context).map_err(|e| e.to_string()),
+        "realpath" => realpath::execute(args, &context).map_err(|e| e.to_string()),
+        "readlink" => readlink::execute(args, &context).map_err(|e| e.to_string()),
+        "mktemp" => mktemp::execute(args, &context).map_err(|e| e.to_string()),
+        "file" => file::execute(args, &context).map_err(|e| e.to_string()),
+        "getfattr" => xattr::execute_getfattr(args, &context).map_err(|e| e.to_string()),
+        "setfattr" => xattr::execute_setfattr(args, &context).map_err(|e| e.to_string()),
+        "dd" => dd::execute(args, &context).map_err(|e| e.to_string()),
+        "tree" => tree::execute(args, &context).map_err(|e| e.to_string()),
+        "sync" => sync_cmd::execute(args, &context).map_err(|e| e.to_string()),
+        "rcp" => sync_cmd::execute_rcp(args, &context).map_err(|e| e.to_string()),
 
         // Text Processing 📝
         "cat" => cat_execute(args, &context).map_err(|e| e.to_string()),
@@ -865,6 +2205,17 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "head" => head_execute(args, &context).map_err(|e| e.to_string()),
         "tail" => tail_execute(args, &context).map_err(|e| e.to_string()),
         "cut" => cut_execute(args, &context).map_err(|e| e.to_string()),
+        "diff" => diff::execute(args, &context).map_err(|e| e.to_string()),
+        "tee" => tee::execute(args, &context).map_err(|e| e.to_string()),
+        "split" => split::execute(args, &context).map_err(|e| e.to_string()),
+        "paste" => paste::execute(args, &context).map_err(|e| e.to_string()),
+        "join" => join::execute(args, &context).map_err(|e| e.to_string()),
+        "comm" => comm::execute(args, &context).map_err(|e| e.to_string()),
+        "nl" => nl::execute(args, &context).map_err(|e| e.to_string()),
+        "tac" => tac::execute(args, &context).map_err(|e| e.to_string()),
+        "rev" => rev::execute(args, &context).map_err(|e| e.to_string()),
+        "less" => less::execute(args, &context).map_err(|e| e.to_string()),
+        "more" => less::execute_more(args, &context).map_err(|e| e.to_string()),
         "tr" => tr_execute(args, &context).map_err(|e| e.to_string()),
         "sort" => sort_execute(args, &context).map_err(|e| e.to_string()),
         "uniq" => uniq_execute(args, &context).map_err(|e| e.to_string()),
@@ -874,17 +2225,29 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "ps" => ps_execute(args, &context).map_err(|e| e.to_string()),
         "kill" => kill_execute(args, &context).map_err(|e| e.to_string()),
         "top" => top_execute(args, &context).map_err(|e| e.to_string()),
+        "pgrep" => pgrep::execute(args, &context).map_err(|e| e.to_string()),
+        "pkill" => pgrep::execute_pkill(args, &context).map_err(|e| e.to_string()),
         "jobs" => jobs_execute(args, &context).map_err(|e| e.to_string()),
         "bg" => bg_execute(args, &context).map_err(|e| e.to_string()),
         "fg" => fg_execute(args, &context).map_err(|e| e.to_string()),
         "free" => free_execute(args, &context).map_err(|e| e.to_string()),
         "uptime" => uptime_execute(args, &context).map_err(|e| e.to_string()),
+        "watch" => watch::execute(args, &context).map_err(|e| e.to_string()),
         "whoami" => whoami_execute(args, &context).map_err(|e| e.to_string()),
 
         // Network Tools 🌐
         "ping" => ping_execute(args, &context).map_err(|e| e.to_string()),
         "curl" => curl_execute(args, &context).map_err(|e| e.to_string()),
         "wget" => wget_execute(args, &context).map_err(|e| e.to_string()),
+        "netstat" => netstat::execute(args, &context).map_err(|e| e.to_string()),
+        "dig" => dig::execute(args, &context).map_err(|e| e.to_string()),
+        "traceroute" => traceroute::execute(args, &context).map_err(|e| e.to_string()),
+        "tracert" => traceroute::execute_tracert(args, &context).map_err(|e| e.to_string()),
+        "nc" => nc::execute(args, &context).map_err(|e| e.to_string()),
+        #[cfg(feature = "remote")]
+        "ssh" => ssh::execute(args, &context).map_err(|e| e.to_string()),
+        #[cfg(feature = "remote")]
+        "scp" => scp::execute(args, &context).map_err(|e| e.to_string()),
 
         // Shell Utilities 🔧
         "which" => which_execute(args, &context).map_err(|e| e.to_string()),
@@ -893,6 +2256,13 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "env" => env_execute(args, &context).map_err(|e| e.to_string()),
         "export" => export_execute(args, &context).map_err(|e| e.to_string()),
         "yes" => yes_execute(args, &context).map_err(|e| e.to_string()),
+        "seq" => seq::execute(args, &context).map_err(|e| e.to_string()),
+        "shuf" => shuf::execute(args, &context).map_err(|e| e.to_string()),
+        "factor" => factor::execute(args, &context).map_err(|e| e.to_string()),
+        "test" => test_builtin::execute(args, &context).map_err(|e| e.to_string()),
+        "[" => test_builtin::execute_bracket(args, &context).map_err(|e| e.to_string()),
+        "ulimit" => ulimit::execute(args, &context).map_err(|e| e.to_string()),
+        "umask" => umask::execute(args, &context).map_err(|e| e.to_string()),
         "true" => {
             // true_execute has legacy signature fn(&[String]) -> Result<i32, String>
             // Call directly if available, else adapt
@@ -907,6 +2277,9 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
 
         // Archive & Compression 📦
         "bzip2" => bzip2_execute(args, &context).map_err(|e| e.to_string()),
+        "gzip" => gzip_execute(args, &context).map_err(|e| e.to_string()),
+        "gunzip" => gunzip_execute(args, &context).map_err(|e| e.to_string()),
+        "zcat" => zcat_execute(args, &context).map_err(|e| e.to_string()),
         "xz" => xz_execute(args, &context).map_err(|e| e.to_string()),
         "zip" => zip_execute(args, &context).map_err(|e| e.to_string()),
         "tar" => tar::execute(args, &context).map_err(|e| e.to_string()),
@@ -927,25 +2300,44 @@ pub fn execute_builtin(command: &str, args: &[String]) -> Result<i32, String> {
         "bc" => bc_execute(args, &context).map_err(|e| e.to_string()),
         "cal" => cal_execute(args, &context).map_err(|e| e.to_string()),
         "cksum" => cksum_execute(args, &context).map_err(|e| e.to_string()),
+        "md5sum" => hashsum::md5sum_execute(args, &context).map_err(|e| e.to_string()),
+        "sha1sum" => hashsum::sha1sum_execute(args, &context).map_err(|e| e.to_string()),
+        "sha256sum" => hashsum::sha256sum_execute(args, &context).map_err(|e| e.to_string()),
+        "sha512sum" => hashsum::sha512sum_execute(args, &context).map_err(|e| e.to_string()),
+        "b2sum" => hashsum::b2sum_execute(args, &context).map_err(|e| e.to_string()),
 
         // System Control 🎛️
         "exec" => exec_execute(args, &context).map_err(|e| e.to_string()),
         "exit" => exit_execute(args, &context).map_err(|e| e.to_string()),
         "eval" => eval_execute(args, &context).map_err(|e| e.to_string()),
+        "elevate" => elevate::execute(args, &context).map_err(|e| e.to_string()),
+        "runas" => elevate::execute_runas(args, &context).map_err(|e| e.to_string()),
+        "sandbox" => sandbox::execute(args, &context).map_err(|e| e.to_string()),
+        #[cfg(feature = "plugins")]
+        "plugin" => plugin::execute(args, &context).map_err(|e| e.to_string()),
 
         // File System Tools 🔧
         "fsck" => fsck_execute(args, &context).map_err(|e| e.to_string()),
         "logstats" => logstats_builtin_execute(args, &context).map_err(|e| e.to_string()),
+        "mount" => mount::execute(args, &context).map_err(|e| e.to_string()),
+        "lsblk" => lsblk::execute(args, &context).map_err(|e| e.to_string()),
 
         // Compression Tools 🗜️
         "zstd" => zstd_execute(args, &context).map_err(|e| e.to_string()),
         "unzstd" => unzstd_execute(args, &context).map_err(|e| e.to_string()),
 
         // System Time Tools ⏰
+        #[cfg(feature = "scheduler")]
+        "schedule" => schedule::execute(args, &context).map_err(|e| e.to_string()),
+        #[cfg(feature = "scheduler")]
+        "at" => schedule::execute_at(args, &context).map_err(|e| e.to_string()),
+        #[cfg(feature = "scheduler")]
+        "cron" => schedule::execute_cron(args, &context).map_err(|e| e.to_string()),
         "timedatectl" => timedatectl_execute(args, &context).map_err(|e| e.to_string()),
 
         // Variable Management Tools 📝
-        "let" | "declare" | "printf" => vars_execute(args, &context).map_err(|e| e.to_string()),
+        "let" | "declare" => vars_execute(args, &context).map_err(|e| e.to_string()),
+        "printf" => printf_execute(args, &context).map_err(|e| e.to_string()),
 
         _ => Err(format!("Unknown builtin command: {command}")),
     }
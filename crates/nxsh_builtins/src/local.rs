@@ -1,39 +1,76 @@
-//! `local` builtin  Edeclare local variables within function scope.
-//! As function scoping is not fully implemented yet, this builtin currently
-//! behaves similarly to `declare` but sets a special `__local_` prefix which
-//! future function frames will interpret as local scope.
+//! `local` builtin - declare variables local to the running function
+//!
+//! Syntax: local [-aAirx] [-p] [NAME[=VALUE] | NAME=(ELEM ...) ...]
+//!
+//! Each NAME starts out unset in the current call and takes its previous
+//! value back when the function returns. Scoping is dynamic: functions
+//! called from here see the local value. Options and operands are those of
+//! `declare`; with no NAMEs the current call's locals are printed.
+//!
+//! Exit status: 0 on success, 1 outside a function or if a NAME is invalid,
+//! 2 on a usage error.
 
-use anyhow::Result;
 use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 
-pub fn local_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    if args.is_empty() {
-        return Ok(());
+/// The `local` builtin command implementation
+pub struct LocalCommand;
+
+impl Builtin for LocalCommand {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Declare variables local to a function"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create variables that exist only while the current function runs, \
+         hiding any variable of the same name until it returns."
+    }
+
+    fn usage(&self) -> &'static str {
+        "local [-aAirx] [-p] [name[=value] ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Declare function-local variables. Accepts the options of declare."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // local creates variables in the current call frame
     }
-    for arg in args {
-        if let Some((name, val)) = arg.split_once('=') {
-            ctx.set_var(format!("__local_{name}"), val);
-        } else {
-            ctx.set_var(format!("__local_{arg}"), "");
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        if !ctx.in_function() {
+            return Ok(ExecutionResult::failure(1)
+                .with_error(b"local: can only be used in a function\n".to_vec()));
         }
+        // `-g` would defeat the point of `local`
+        let mut options = args.iter().take_while(|a| a.starts_with('-'));
+        if options.any(|a| a.contains('g')) {
+            return Ok(ExecutionResult::failure(2).with_error(
+                format!(
+                    "local: -g: invalid option\nlocal: usage: {}\n",
+                    self.usage()
+                )
+                .into_bytes(),
+            ));
+        }
+        Ok(crate::declare::run_as(ctx, args, "local", self.usage()))
     }
-    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn set_local() {
-        let ctx = ShellContext::new();
-        local_cli(&["foo=bar".into()], &ctx).unwrap();
-        assert_eq!(ctx.get_var("__local_foo").unwrap(), "bar");
+impl LocalCommand {
+    /// Create a new local command instance
+    pub fn new() -> Self {
+        LocalCommand
     }
-} 
-
+}
 
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+impl Default for LocalCommand {
+    fn default() -> Self {
+        Self::new()
+    }
 }
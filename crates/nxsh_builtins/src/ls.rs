@@ -25,6 +25,9 @@
 //!   -c                     - Sort by change time
 //!   -u                     - Sort by access time
 //!   --group-directories-first - Group directories before files
+//!   -@                     - Mark entries with extended attributes, data
+//!                            streams (`@`) or an ACL (`+`) in long format,
+//!                            and list the attributes with their sizes
 
 use super::ui_design::{
     Alignment, Animation, BorderStyle, Colorize, Notification, TableFormatter, TableOptions,
@@ -34,8 +37,12 @@ use chrono::{DateTime, Local};
 use humansize::{format_size, BINARY};
 use nu_ansi_term::{Color as NuColor, Style};
 use nxsh_core::memory_efficient::MemoryEfficientStringBuilder;
+use nxsh_hal::magic::FileClass;
+use nxsh_ui::pager::{self, PagerOptions};
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::fs::{self, Metadata};
+use std::io::{self, Write as _};
 #[cfg(unix)]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 #[cfg(windows)]
@@ -542,6 +549,7 @@ pub struct LsOptions {
     pub full_time: bool,
     pub group_dirs_first: bool,
     pub git_status: bool,
+    pub show_xattrs: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -612,6 +620,7 @@ impl Default for LsOptions {
             full_time: false,
             group_dirs_first: false,
             git_status: true,
+            show_xattrs: false,
         }
     }
 }
@@ -647,16 +656,28 @@ pub fn ls_cli(args: &[String]) -> Result<()> {
         None
     };
 
+    let mut out = String::new();
     for (i, path) in paths.iter().enumerate() {
         if i > 0 {
-            println!();
+            writeln!(out)?;
         }
 
-        if paths.len() > 1 {
-            println!("{}:", path.display());
+        if paths.len() > 1 || options.recursive {
+            writeln!(out, "{}:", path.display())?;
         }
 
-        list_directory(path, &options, use_colors, git_repo.as_ref())?;
+        list_directory(path, &options, use_colors, git_repo.as_ref(), &mut out)?;
+    }
+
+    // A recursive listing easily runs past the screen
+    if options.recursive && pager::should_page(&out) {
+        let options = PagerOptions {
+            raw: true,
+            ..Default::default()
+        };
+        pager::page(&out, options, &mut io::stdout())?;
+    } else {
+        io::stdout().write_all(out.as_bytes())?;
     }
 
     Ok(())
@@ -736,6 +757,7 @@ fn parse_ls_args(args: &[String]) -> Result<(LsOptions, Vec<String>)> {
                         }
                         'c' => options.sort_by_ctime = true,
                         'u' => options.sort_by_atime = true,
+                        '@' => options.show_xattrs = true,
                         _ => return Err(anyhow!("ls: unknown option '-{}'", ch)),
                     }
                 }
@@ -763,14 +785,15 @@ fn list_directory(
     options: &LsOptions,
     use_colors: bool,
     git_repo: Option<&GitRepository>,
+    out: &mut String,
 ) -> Result<()> {
     if options.directory_only {
         // Just list the directory itself
         let file_info = get_file_info(path, git_repo)?;
         if options.long_format {
-            print_long_format(&[file_info], options, use_colors)?;
+            print_long_format(&[file_info], options, use_colors, out)?;
         } else {
-            print_short_format(&[file_info], options, use_colors)?;
+            print_short_format(&[file_info], options, use_colors, out)?;
         }
         return Ok(());
     }
@@ -785,9 +808,21 @@ fn list_directory(
     sort_entries(&mut sorted_entries, options);
 
     if options.long_format {
-        print_long_format(&sorted_entries, options, use_colors)?;
+        print_long_format(&sorted_entries, options, use_colors, out)?;
     } else {
-        print_short_format(&sorted_entries, options, use_colors)?;
+        print_short_format(&sorted_entries, options, use_colors, out)?;
+    }
+
+    if options.recursive {
+        // Symlinked directories are listed but not followed, so loops end
+        for entry in &sorted_entries {
+            if !entry.metadata.is_dir() || entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            writeln!(out)?;
+            writeln!(out, "{}:", entry.path.display())?;
+            list_directory(&entry.path, options, use_colors, git_repo, out)?;
+        }
     }
 
     Ok(())
@@ -894,7 +929,12 @@ fn sort_entries(entries: &mut [FileInfo], options: &LsOptions) {
     });
 }
 
-fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool) -> Result<()> {
+fn print_long_format(
+    entries: &[FileInfo],
+    options: &LsOptions,
+    use_colors: bool,
+    out: &mut String,
+) -> Result<()> {
     if entries.is_empty() {
         return Ok(());
     }
@@ -946,6 +986,9 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
     headers.push("Size".to_string());
     headers.push("Modified".to_string());
     headers.push("Name".to_string());
+    if options.show_xattrs {
+        headers.push("Attributes".to_string());
+    }
 
     // Prepare table rows
     let mut rows = Vec::new();
@@ -964,7 +1007,18 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
         #[cfg(windows)]
         let mode = 0o755; // Default for Windows
 
-        row.push(formatter.format_permissions(mode));
+        let attributes = if options.show_xattrs {
+            extended_attributes(entry)
+        } else {
+            Vec::new()
+        };
+        let mut permissions = formatter.format_permissions(mode);
+        if !attributes.is_empty() {
+            permissions.push_str(&"@".info());
+        } else if options.show_xattrs && has_extended_acl(entry) {
+            permissions.push_str(&"+".info());
+        }
+        row.push(permissions);
 
         // Links
         row.push(get_nlink(&entry.metadata).to_string().info());
@@ -1030,19 +1084,12 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
         row.push(time_str.dim());
 
         // Name with icon
-        let icon = formatter.get_file_icon(
-            entry
-                .path
-                .file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or(""),
-        );
+        let icon = file_icon(&entry.path, &entry.metadata);
         let name_with_icon = if use_colors {
             let colored_name = format_file_name(entry, use_colors, false);
             let mut result =
                 MemoryEfficientStringBuilder::with_capacity(icon.len() + colored_name.len() + 1);
-            result.push_str(&icon);
+            result.push_str(icon);
             result.push(' ');
             result.push_str(&colored_name);
             result.into_string()
@@ -1050,7 +1097,7 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
             let filename = entry.path.file_name().unwrap_or_default().to_string_lossy();
             let mut result =
                 MemoryEfficientStringBuilder::with_capacity(icon.len() + filename.len() + 1);
-            result.push_str(&icon);
+            result.push_str(icon);
             result.push(' ');
             result.push_str(&filename);
             result.into_string()
@@ -1077,11 +1124,18 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
         };
 
         row.push(final_name);
+        if options.show_xattrs {
+            let listed: Vec<String> = attributes
+                .iter()
+                .map(|(name, size)| format!("{name} ({size})"))
+                .collect();
+            row.push(listed.join(", ").muted());
+        }
         rows.push(row);
     }
 
     // Print the beautiful advanced table
-    print!("{}", formatter.create_advanced_table(&headers, &rows));
+    out.push_str(&formatter.create_advanced_table(&headers, &rows));
 
     // Show summary for large directories
     if entries.len() > 50 {
@@ -1095,6 +1149,41 @@ fn print_long_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool
     Ok(())
 }
 
+/// The extended attributes and alternate data streams of an entry, with
+/// the size of each value. The attributes that hold an ACL are left out;
+/// `has_extended_acl` covers those.
+fn extended_attributes(entry: &FileInfo) -> Vec<(String, u64)> {
+    use nxsh_hal::fs::{alternate_streams, get_xattr, list_xattrs};
+
+    let mut names = list_xattrs(&entry.path, false).unwrap_or_default();
+    names.retain(|name| !name.to_string_lossy().starts_with("system.posix_acl_"));
+    names.sort();
+    let mut attributes: Vec<(String, u64)> = names
+        .into_iter()
+        .map(|name| {
+            let size = get_xattr(&entry.path, &name, false)
+                .ok()
+                .flatten()
+                .map_or(0, |value| value.len() as u64);
+            (name.to_string_lossy().into_owned(), size)
+        })
+        .collect();
+    if !entry.is_symlink {
+        let streams = alternate_streams(&entry.path).unwrap_or_default();
+        attributes.extend(streams.into_iter().map(|stream| (stream.name, stream.size)));
+    }
+    attributes
+}
+
+/// Whether an entry's ACL grants or denies more than its mode bits
+fn has_extended_acl(entry: &FileInfo) -> bool {
+    !entry.is_symlink
+        && nxsh_hal::fs::acl(&entry.path)
+            .ok()
+            .flatten()
+            .is_some_and(|acl| acl.is_extended())
+}
+
 fn print_long_entry(
     entry: &FileInfo,
     options: &LsOptions,
@@ -1241,9 +1330,12 @@ fn print_long_entry(
     Ok(())
 }
 
-fn print_short_format(entries: &[FileInfo], options: &LsOptions, use_colors: bool) -> Result<()> {
-    let formatter = TableFormatter::new();
-
+fn print_short_format(
+    entries: &[FileInfo],
+    options: &LsOptions,
+    use_colors: bool,
+    out: &mut String,
+) -> Result<()> {
     if options.one_per_line {
         for entry in entries {
             let mut line = String::new();
@@ -1266,17 +1358,10 @@ fn print_short_format(entries: &[FileInfo], options: &LsOptions, use_colors: boo
             }
 
             // Add file icon
-            let icon = formatter.get_file_icon(
-                entry
-                    .path
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or(""),
-            );
+            let icon = file_icon(&entry.path, &entry.metadata);
             // Pre-calculate capacity for optimal memory usage: icon (typically 1-4 chars) + 1 space
             let mut icon_buf = MemoryEfficientStringBuilder::new(6);
-            icon_buf.push_str(&icon);
+            icon_buf.push_str(icon);
             icon_buf.push(' ');
             line.push_str(&icon_buf.into_string());
 
@@ -1284,14 +1369,14 @@ fn print_short_format(entries: &[FileInfo], options: &LsOptions, use_colors: boo
             let colored_name = format_file_name(entry, use_colors, options.classify);
             line.push_str(&colored_name);
 
-            println!("{line}");
+            writeln!(out, "{line}")?;
         }
     } else {
         // Multi-column output with beautiful formatting
         let term_width = terminal_size::terminal_size()
             .map(|(w, _)| w.0 as usize)
             .unwrap_or(80);
-        print_beautiful_columns(entries, options, use_colors, term_width)?;
+        print_beautiful_columns(entries, options, use_colors, term_width, out)?;
     }
 
     Ok(())
@@ -1373,8 +1458,8 @@ fn print_beautiful_columns(
     options: &LsOptions,
     use_colors: bool,
     term_width: usize,
+    out: &mut String,
 ) -> Result<()> {
-    let formatter = TableFormatter::new();
     let mut items = Vec::new();
     let mut max_width = 0;
 
@@ -1405,17 +1490,10 @@ fn print_beautiful_columns(
         }
 
         // Add beautiful icon
-        let icon = formatter.get_file_icon(
-            entry
-                .path
-                .file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or(""),
-        );
+        let icon = file_icon(&entry.path, &entry.metadata);
         // Pre-calculate capacity for optimal memory usage: icon (typically 1-4 chars) + 1 space
         let mut icon_buf = MemoryEfficientStringBuilder::new(6);
-        icon_buf.push_str(&icon);
+        icon_buf.push_str(icon);
         icon_buf.push(' ');
         item.push_str(&icon_buf.into_string());
         plain_item.push_str("🗎 "); // Use a consistent width placeholder for icons
@@ -1455,13 +1533,13 @@ fn print_beautiful_columns(
                 }
             }
         }
-        println!("{line}");
+        writeln!(out, "{line}")?;
     }
 
     Ok(())
 }
 
-fn format_permissions(metadata: &Metadata) -> String {
+pub(crate) fn format_permissions(metadata: &Metadata) -> String {
     let mode = get_mode(&metadata.permissions());
     let mut perms = String::with_capacity(10);
 
@@ -1546,6 +1624,31 @@ fn format_time(metadata: &Metadata, time_style: &TimeStyle, full_time: bool) ->
     }
 }
 
+/// Icon for an entry, chosen from the content of regular files; `metadata`
+/// is the entry's own, not that of a symlink's target
+pub(crate) fn file_icon(path: &Path, metadata: &Metadata) -> &'static str {
+    if metadata.file_type().is_symlink() {
+        return "🔗";
+    }
+    if metadata.is_dir() {
+        return "📁";
+    }
+    if !metadata.is_file() {
+        return "📄";
+    }
+    match nxsh_hal::magic::identify_path(path).map(|magic| magic.class) {
+        Ok(FileClass::Executable) => "🔧",
+        Ok(FileClass::Script) => "📜",
+        Ok(FileClass::Archive) => "📦",
+        Ok(FileClass::Image) => "🎨",
+        Ok(FileClass::Audio) => "🎵",
+        Ok(FileClass::Video) => "🎬",
+        Ok(FileClass::Document) => "📕",
+        Ok(FileClass::Text) => "📝",
+        _ => "📄",
+    }
+}
+
 fn format_file_name(entry: &FileInfo, use_colors: bool, classify: bool) -> String {
     let mut name = entry.name.clone();
 
@@ -1,21 +1,295 @@
-//! `lsblk` builtin  Elist block devices.
+//! `lsblk` builtin - list block devices
 //!
-//! Delegates to system `lsblk` binary for complete output and option support.
-//! No fallback implementation is provided since querying block devices is
-//! platform-specific.
+//! Syntax:
+//!   lsblk [-abdfl] [DEVICE...]
+//!
+//! Lists the disks with their partitions drawn below each, showing the
+//! device numbers, size, whether they are removable or read only, their
+//! type and where they are mounted. `-f` shows the file systems instead:
+//! type, label and UUID. Devices without any space are left out unless
+//! `-a` is given. `-b` prints sizes in bytes, `-d` leaves out the
+//! partitions and `-l` lists the devices without the tree. DEVICEs, by
+//! name or as `/dev` paths, list only those devices and their partitions.
+//!
+//! The devices come from [`nxsh_hal::mounts`]: `/sys/class/block` and the
+//! udev database on Linux. Other systems have no such table, and there
+//! only the mounted volumes are listed.
+//!
+//! In a structured pipeline `lsblk` takes the same `-a`, `-d` and DEVICE
+//! arguments and gives a table with the columns name, parent, type, size,
+//! removable, read_only, fstype, label, uuid and mountpoints:
+//!
+//!   lsblk | where removable -eq true | select name size mountpoints
+//!
+//! The exit status is 0 on success and 1 for an invalid option, an
+//! unknown DEVICE, or when the devices could not be read.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::mounts::list_block_devices;
+use nxsh_hal::BlockDevice;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The `lsblk` builtin command implementation
+pub struct LsblkCommand;
+
+impl Builtin for LsblkCommand {
+    fn name(&self) -> &'static str {
+        "lsblk"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "List block devices"
+    }
+
+    fn description(&self) -> &'static str {
+        "List disks and their partitions with size, type and mount points, or the file \
+         systems on them with their labels and UUIDs."
+    }
+
+    fn usage(&self) -> &'static str {
+        "lsblk [-abdfl] [DEVICE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List block devices. Use 'lsblk -f' for the file systems and their UUIDs, or \
+         'lsblk | where removable -eq true' in a structured pipeline."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
+    }
+}
+
+/// Run lsblk for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
+}
+
+/// The devices `args` select, for the structured `lsblk`
+pub(crate) fn selected_devices(args: &[String]) -> Result<Vec<BlockDevice>, String> {
+    Options::parse(args)?.devices()
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+fn run(args: &[String]) -> Outcome {
+    let listing = Options::parse(args).and_then(|options| {
+        let devices = options.devices()?;
+        Ok(render(&devices, &options))
+    });
+    match listing {
+        Ok(stdout) => Outcome {
+            stdout,
+            stderr: String::new(),
+            status: 0,
+        },
+        Err(message) => Outcome {
+            stdout: String::new(),
+            stderr: format!("lsblk: {message}\n"),
+            status: 1,
+        },
+    }
+}
 
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
+/// Parsed command line
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// `-a`: devices without space too
+    all: bool,
+    /// `-b`: sizes in bytes
+    bytes: bool,
+    /// `-d`: no partitions
+    nodeps: bool,
+    /// `-f`: the file system columns
+    filesystems: bool,
+    /// `-l`: no tree
+    list: bool,
+    devices: Vec<String>,
+}
 
-pub fn lsblk_cli(args: &[String]) -> Result<()> {
-    if let Ok(path) = which("lsblk") {
-        let status = Command::new(path)
-            .args(args)
-            .status()
-            .map_err(|e| anyhow!("lsblk: failed to launch backend: {e}"))?;
-        std::process::exit(status.code().unwrap_or(1));
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        for arg in args {
+            match arg.as_str() {
+                "--all" => options.all = true,
+                "--bytes" => options.bytes = true,
+                "--nodeps" => options.nodeps = true,
+                "--fs" => options.filesystems = true,
+                "--list" => options.list = true,
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 => {
+                    for flag in flags.chars().skip(1) {
+                        match flag {
+                            'a' => options.all = true,
+                            'b' => options.bytes = true,
+                            'd' => options.nodeps = true,
+                            'f' => options.filesystems = true,
+                            'l' => options.list = true,
+                            other => return Err(format!("invalid option -- '{other}'")),
+                        }
+                    }
+                }
+                _ => options.devices.push(arg.clone()),
+            }
+        }
+        Ok(options)
     }
-    Err(anyhow!("lsblk: backend not found; please install util-linux"))
-} 
 
+    fn devices(&self) -> Result<Vec<BlockDevice>, String> {
+        let devices = list_block_devices().map_err(|e| e.to_string())?;
+        let named = |device: &BlockDevice, wanted: &str| {
+            device.name == wanted || device.path == Path::new(wanted)
+        };
+        if let Some(unknown) = self
+            .devices
+            .iter()
+            .find(|wanted| !devices.iter().any(|device| named(device, wanted)))
+        {
+            return Err(format!("{unknown}: not a block device"));
+        }
+        Ok(devices
+            .iter()
+            .filter(|device| self.all || device.size > 0)
+            .filter(|device| !self.nodeps || device.parent.is_none())
+            .filter(|device| {
+                self.devices.is_empty()
+                    || self.devices.iter().any(|wanted| {
+                        named(device, wanted)
+                            || device.parent.as_ref().is_some_and(|parent| {
+                                devices
+                                    .iter()
+                                    .any(|disk| &disk.name == parent && named(disk, wanted))
+                            })
+                    })
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+/// A size as `lsblk` shows it, with a unit unless `-b` was given
+fn size(bytes: u64, options: &Options) -> String {
+    if options.bytes {
+        bytes.to_string()
+    } else if bytes < 1024 {
+        format!("{bytes}B")
+    } else {
+        crate::df::human_size(bytes, 1024)
+    }
+}
+
+/// The table `lsblk` prints, a device with several mount points taking a
+/// line for each
+fn render(devices: &[BlockDevice], options: &Options) -> String {
+    let header: &[&str] = if options.filesystems {
+        &["NAME", "FSTYPE", "LABEL", "UUID", "MOUNTPOINTS"]
+    } else {
+        &["NAME", "MAJ:MIN", "RM", "SIZE", "RO", "TYPE", "MOUNTPOINTS"]
+    };
+    // Columns aligned to the right, the rest go to the left
+    let right: &[usize] = if options.filesystems {
+        &[]
+    } else {
+        &[1, 2, 3, 4]
+    };
+    let flag = |set: bool| if set { "1" } else { "0" }.to_string();
+
+    let mut rows = vec![header
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()];
+    let mut disk: Option<&str> = None;
+    for (i, device) in devices.iter().enumerate() {
+        // Partitions are drawn below their disk, the last with a corner
+        let name = match &device.parent {
+            Some(parent) if !options.list && disk == Some(parent.as_str()) => {
+                let last = devices
+                    .get(i + 1)
+                    .is_none_or(|next| next.parent.as_ref() != Some(parent));
+                format!("{}{}", if last { "└─" } else { "├─" }, device.name)
+            }
+            _ => device.name.clone(),
+        };
+        if device.parent.is_none() {
+            disk = Some(&device.name);
+        }
+        let mut mount_points = device
+            .mount_points
+            .iter()
+            .map(|mount| mount.display().to_string());
+        let first_mount = mount_points.next().unwrap_or_default();
+        rows.push(if options.filesystems {
+            vec![
+                name,
+                device.fs_type.clone().unwrap_or_default(),
+                device.label.clone().unwrap_or_default(),
+                device.uuid.clone().unwrap_or_default(),
+                first_mount,
+            ]
+        } else {
+            vec![
+                name,
+                device
+                    .device_number
+                    .map_or("-".to_string(), |(major, minor)| format!("{major}:{minor}")),
+                flag(device.removable),
+                size(device.size, options),
+                flag(device.read_only),
+                device.kind.name().to_string(),
+                first_mount,
+            ]
+        });
+        for mount in mount_points {
+            let mut row = vec![String::new(); header.len()];
+            row[header.len() - 1] = mount;
+            rows.push(row);
+        }
+    }
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (column, cell) in row.iter().enumerate() {
+            let width = widths[column];
+            if column > 0 {
+                line.push(' ');
+            }
+            if column == header.len() - 1 {
+                line.push_str(cell);
+            } else if right.contains(&column) {
+                line.push_str(&format!("{cell:>width$}"));
+            } else {
+                line.push_str(&format!("{cell:<width$}"));
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
@@ -349,9 +349,8 @@ fn set_selinux_context(path: &Path, context: &str) -> Result<()> {
     {
         // Try to set SELinux context via xattr "security.selinux"
         // This requires appropriate privileges and SELinux enabled.
-        use nix::sys::xattr;
         let name = "security.selinux";
-        xattr::set(path, name, context.as_bytes(), xattr::XattrFlags::empty()).map_err(|e| {
+        nxsh_hal::fs::set_xattr(path, name, context.as_bytes(), true).map_err(|e| {
             anyhow!(
                 "mkdir: failed to set SELinux context on '{}': {}",
                 path.display(),
@@ -0,0 +1,403 @@
+//! `mktemp` builtin - create temporary files and directories
+//!
+//! Syntax:
+//!   mktemp [-d] [-u] [-q] [-p DIR|--tmpdir[=DIR]] [-t] [--suffix=SUFF] [TEMPLATE]
+//!
+//! Creates a file named after TEMPLATE with its run of at least three
+//! trailing `X`s replaced by random letters and digits, and prints the
+//! name. Text after the last `X` is kept as a suffix, as is `--suffix`.
+//! Without TEMPLATE `tmp.XXXXXXXXXX` is made in the temporary directory.
+//! `-d` makes a directory instead, `-u` only prints a name that was free
+//! and `-q` hides error messages. `-p DIR` or `--tmpdir` puts TEMPLATE
+//! under DIR, `$TMPDIR` or the system temporary directory, and `-t` does
+//! the same for a bare file name.
+//!
+//! Files are created through `nxsh_hal::fs` readable only by their owner.
+//!
+//! The exit status is 0 on success and 1 on failure.
+
+use crate::common::{hal_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{FileSystem, HalOpenOptions};
+use nxsh_hal::HalError;
+use rand::rngs::OsRng;
+use rand::Rng;
+use std::io::{self, Write};
+use std::path::{is_separator, Path, PathBuf};
+
+/// Names tried before giving up when every one is taken
+const ATTEMPTS: usize = 100;
+
+/// Characters an `X` can become
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// The `mktemp` builtin command implementation
+pub struct MktempCommand;
+
+impl Builtin for MktempCommand {
+    fn name(&self) -> &'static str {
+        "mktemp"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Create temporary files and directories"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create a uniquely named file or directory from TEMPLATE, whose trailing X's are \
+         replaced by random characters, and print its name."
+    }
+
+    fn usage(&self) -> &'static str {
+        "mktemp [-d] [-u] [-q] [-p DIR|--tmpdir[=DIR]] [-t] [--suffix=SUFF] [TEMPLATE]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create temporary files and directories. Use 'dir=$(mktemp -d)' for a scratch \
+         directory or 'mktemp -p . build.XXXX' for a file under the current one."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let tmpdir = ctx.get_var("TMPDIR");
+        let outcome = run(args, &cwd, tmpdir.as_deref());
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run mktemp for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let tmpdir = std::env::var("TMPDIR").ok();
+    let outcome = run(args, &cwd, tmpdir.as_deref());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    directory: bool,
+    dry_run: bool,
+    quiet: bool,
+    /// `-p DIR` or `--tmpdir`, with its DIR when one was given
+    tmpdir: Option<Option<String>>,
+    bare_name: bool,
+    suffix: Option<String>,
+    template: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            directory: false,
+            dry_run: false,
+            quiet: false,
+            tmpdir: None,
+            bare_name: false,
+            suffix: None,
+            template: None,
+        };
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--directory" => options.directory = true,
+                "--dry-run" => options.dry_run = true,
+                "--quiet" => options.quiet = true,
+                "--tmpdir" => options.tmpdir = Some(None),
+                "--suffix" => {
+                    let suffix = iter
+                        .next()
+                        .ok_or_else(|| "option '--suffix' requires an argument".to_string())?;
+                    options.suffix = Some(suffix.clone());
+                }
+                long if long.starts_with("--tmpdir=") => {
+                    options.tmpdir = Some(Some(long["--tmpdir=".len()..].to_string()));
+                }
+                long if long.starts_with("--suffix=") => {
+                    options.suffix = Some(long["--suffix=".len()..].to_string());
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'd' => options.directory = true,
+                            'u' => options.dry_run = true,
+                            'q' => options.quiet = true,
+                            't' => options.bare_name = true,
+                            'p' => {
+                                let attached = &short[2 + at..];
+                                let dir = match attached {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        "option requires an argument -- 'p'".to_string()
+                                    })?,
+                                    _ => attached.to_string(),
+                                };
+                                options.tmpdir = Some(Some(dir));
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
+        if operands.len() > 1 {
+            return Err("too many templates".to_string());
+        }
+        options.template = operands.pop();
+        Ok(options)
+    }
+}
+
+/// A template split around its run of X's
+#[derive(Debug, PartialEq)]
+struct Template<'t> {
+    prefix: &'t str,
+    length: usize,
+    suffix: String,
+}
+
+impl<'t> Template<'t> {
+    fn parse(template: &'t str, suffix: Option<&str>) -> Result<Self, String> {
+        let (stem, suffix) = match suffix {
+            Some(suffix) => {
+                if !template.ends_with('X') {
+                    return Err(format!(
+                        "with --suffix, template '{template}' must end in X"
+                    ));
+                }
+                (template, suffix.to_string())
+            }
+            None => {
+                let end = template.rfind('X').map_or(0, |at| at + 1);
+                (&template[..end], template[end..].to_string())
+            }
+        };
+        if suffix.contains(is_separator) {
+            return Err(format!(
+                "invalid suffix '{suffix}', contains directory separator"
+            ));
+        }
+        let prefix = stem.trim_end_matches('X');
+        let length = stem.len() - prefix.len();
+        if length < 3 {
+            return Err(format!("too few X's in template '{template}'"));
+        }
+        Ok(Template {
+            prefix,
+            length,
+            suffix,
+        })
+    }
+
+    /// The template with its X's replaced at random
+    fn fill(&self) -> String {
+        let mut name = String::with_capacity(self.prefix.len() + self.length + self.suffix.len());
+        name.push_str(self.prefix);
+        for _ in 0..self.length {
+            name.push(ALPHABET[OsRng.gen_range(0..ALPHABET.len())] as char);
+        }
+        name.push_str(&self.suffix);
+        name
+    }
+}
+
+/// Whether ERROR says the name was already taken
+fn already_exists(error: &HalError) -> bool {
+    matches!(error, HalError::Io(io) if io.kind == io::ErrorKind::AlreadyExists)
+}
+
+/// Create the file or directory at PATH, or with `-u` just check it is free
+fn create(fs: &FileSystem, path: &Path, options: &Options) -> Result<(), HalError> {
+    if options.dry_run {
+        return match fs.symlink_metadata(path) {
+            Ok(_) => Err(HalError::io_error(
+                "mktemp",
+                None,
+                io::ErrorKind::AlreadyExists.into(),
+            )),
+            Err(_) => Ok(()),
+        };
+    }
+    if options.directory {
+        return fs.create_dir(path, 0o700);
+    }
+    let mut open = HalOpenOptions::new();
+    open.write(true).create_new(true);
+    #[cfg(unix)]
+    open.mode(0o600);
+    fs.open(path, &open).map(drop)
+}
+
+fn run(args: &[String], cwd: &Path, tmpdir: Option<&str>) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let fail = |mut outcome: Outcome, message: String, quiet: bool| {
+        if !quiet {
+            outcome.stderr = format!("mktemp: {message}\n").into_bytes();
+        }
+        outcome.status = 1;
+        outcome
+    };
+    let mut options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return fail(outcome, message, false),
+    };
+    let quiet = options.quiet;
+
+    // No template means a default name in the temporary directory
+    let template = match options.template.take() {
+        Some(template) => template,
+        None => {
+            if options.tmpdir.is_none() {
+                options.bare_name = true;
+            }
+            "tmp.XXXXXXXXXX".to_string()
+        }
+    };
+    let temporary = || {
+        tmpdir
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+    };
+    let dir = match &options.tmpdir {
+        Some(given) => {
+            if Path::new(&template).is_absolute() {
+                let message = format!(
+                    "invalid template, '{template}'; with --tmpdir, it may not be absolute"
+                );
+                return fail(outcome, message, quiet);
+            }
+            Some(
+                given
+                    .as_ref()
+                    .filter(|dir| !dir.is_empty())
+                    .map_or_else(temporary, PathBuf::from),
+            )
+        }
+        None if options.bare_name => Some(temporary()),
+        None => None,
+    };
+    if options.bare_name && template.contains(is_separator) {
+        let message = format!("invalid template, '{template}', contains directory separator");
+        return fail(outcome, message, quiet);
+    }
+    let parsed = match Template::parse(&template, options.suffix.as_deref()) {
+        Ok(parsed) => parsed,
+        Err(message) => return fail(outcome, message, quiet),
+    };
+    let fs = match FileSystem::new() {
+        Ok(fs) => fs,
+        Err(e) => return fail(outcome, hal_message(&e), quiet),
+    };
+
+    let mut last = None;
+    for _ in 0..ATTEMPTS {
+        let name = parsed.fill();
+        let shown = match &dir {
+            Some(dir) => dir.join(&name),
+            None => PathBuf::from(&name),
+        };
+        match create(&fs, &cwd.join(&shown), &options) {
+            Ok(()) => {
+                outcome.stdout.extend(shown.to_string_lossy().as_bytes());
+                outcome.stdout.push(b'\n');
+                return outcome;
+            }
+            Err(e) if already_exists(&e) => last = Some(e),
+            Err(e) => {
+                last = Some(e);
+                break;
+            }
+        }
+    }
+    let kind = if options.directory {
+        "directory"
+    } else {
+        "file"
+    };
+    let reason = last.map_or_else(String::new, |e| hal_message(&e));
+    let message = format!("failed to create {kind} via template '{template}': {reason}");
+    fail(outcome, message, quiet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mktemp(args: &[&str], dir: &Path) -> (String, String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, Some(&dir.display().to_string()));
+        (
+            String::from_utf8(outcome.stdout).unwrap(),
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    #[test]
+    fn splits_templates() {
+        let template = Template::parse("a.XXXX.txt", None).unwrap();
+        assert_eq!((template.prefix, template.length), ("a.", 4));
+        assert_eq!(template.suffix, ".txt");
+        let name = template.fill();
+        assert!(name.starts_with("a.") && name.ends_with(".txt") && name.len() == 10);
+
+        assert_eq!(Template::parse("aXXXX", Some(".c")).unwrap().suffix, ".c");
+        assert!(Template::parse("aXX", None).is_err());
+        assert!(Template::parse("aXXXb", Some(".c")).is_err());
+        assert!(Template::parse("XXX/a", None).is_err());
+    }
+
+    #[test]
+    fn creates_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let (out, err, status) = mktemp(&[], root);
+        assert_eq!(status, 0, "{err}");
+        let file = PathBuf::from(out.trim_end());
+        assert_eq!(file.parent(), Some(root));
+        assert!(file.is_file());
+
+        let (out, _, _) = mktemp(&["-d", "work.XXXXXX"], root);
+        assert!(out.starts_with("work.") && root.join(out.trim_end()).is_dir());
+
+        let (out, _, _) = mktemp(&["-u", "-p", "sub", "n.XXX"], root);
+        let sub = format!("sub{}n.", std::path::MAIN_SEPARATOR);
+        assert!(out.starts_with(&sub));
+        assert!(!root.join(out.trim_end()).exists());
+
+        let (_, err, status) = mktemp(&["-t", "a/XXXX"], root);
+        assert_eq!(status, 1);
+        assert!(err.contains("contains directory separator"));
+        assert_eq!(
+            mktemp(&["-q", "missing/XXX"], root),
+            (String::new(), String::new(), 1)
+        );
+    }
+}
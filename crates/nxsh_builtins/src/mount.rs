@@ -7,13 +7,23 @@
 //! - Pure Rust implementation with comprehensive mount option support
 //! - Safe mounting operations with privilege checking and error handling
 //! - Enterprise-grade cross-platform compatibility with appropriate fallbacks
+//!
+//! The mounted file systems are listed from [`nxsh_hal::mounts`], which reads
+//! `/proc/self/mountinfo` on Linux, `getmntinfo` on macOS and the volume APIs
+//! on Windows. In a structured pipeline `mount [-a] [-t TYPES]` gives a table
+//! with the columns source, mount, type, options, device, uuid and label.
 
+use crate::common::{BuiltinContext, BuiltinResult};
 use anyhow::{anyhow, bail, Context, Result};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::mounts::fs_usage;
+use nxsh_hal::MountEntry;
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::io::{self, Write};
+use std::path::Path;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::process::Command;
 
 /// Comprehensive filesystem mount information structure
 #[derive(Debug, Clone)]
@@ -36,6 +46,8 @@ pub struct MountInfo {
     pub major_minor: Option<String>,
     pub root: Option<String>,
     pub mount_source: Option<String>,
+    pub uuid: Option<String>,
+    pub label: Option<String>,
 }
 
 impl Default for MountInfo {
@@ -59,6 +71,8 @@ impl Default for MountInfo {
             major_minor: None,
             root: None,
             mount_source: None,
+            uuid: None,
+            label: None,
         }
     }
 }
@@ -216,253 +230,6 @@ impl MountConfig {
 mod windows_impl {
     use super::*;
 
-    pub fn list_mounts() -> Result<Vec<MountInfo>> {
-        let mut mounts = Vec::new();
-
-        // Try WMI first for comprehensive drive information
-        if let Ok(wmi_mounts) = query_wmi_logical_disks() {
-            mounts.extend(wmi_mounts);
-        }
-
-        // Try PowerShell Get-Volume for additional information
-        if let Ok(ps_volumes) = query_powershell_volumes() {
-            enhance_mounts_with_volumes(&mut mounts, ps_volumes);
-        }
-
-        // Add network drives
-        if let Ok(network_drives) = query_network_drives() {
-            mounts.extend(network_drives);
-        }
-
-        Ok(mounts)
-    }
-
-    fn query_wmi_logical_disks() -> Result<Vec<MountInfo>> {
-        let output = Command::new("powershell")
-            .args([
-                "-NoProfile",
-                "-Command",
-                r#"
-                Get-WmiObject -Class Win32_LogicalDisk | ForEach-Object {
-                    [PSCustomObject]@{
-                        DeviceID = $_.DeviceID
-                        FileSystem = $_.FileSystem
-                        Size = $_.Size
-                        FreeSpace = $_.FreeSpace
-                        DriveType = $_.DriveType
-                        VolumeName = $_.VolumeName
-                        VolumeSerialNumber = $_.VolumeSerialNumber
-                        Description = $_.Description
-                        Compressed = $_.Compressed
-                        SupportsFileBasedCompression = $_.SupportsFileBasedCompression
-                    }
-                } | ConvertTo-Json -Depth 2
-                "#,
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            bail!(
-                "WMI logical disk query failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-
-        let json_str = String::from_utf8(output.stdout)?;
-        let disks_json: Value =
-            serde_json::from_str(&json_str).context("Failed to parse WMI logical disk JSON")?;
-
-        let mut mounts = Vec::new();
-        let disk_array = if disks_json.is_array() {
-            disks_json.as_array().unwrap()
-        } else {
-            &vec![disks_json]
-        };
-
-        for disk_json in disk_array {
-            let device_id = disk_json["DeviceID"]
-                .as_str()
-                .unwrap_or("Unknown")
-                .to_string();
-            let filesystem = disk_json["FileSystem"]
-                .as_str()
-                .unwrap_or("Unknown")
-                .to_string();
-            let size = disk_json["Size"].as_u64();
-            let free_space = disk_json["FreeSpace"].as_u64();
-            let drive_type = disk_json["DriveType"].as_u64().unwrap_or(0);
-
-            let mut options = Vec::new();
-            if disk_json["Compressed"].as_bool().unwrap_or(false) {
-                options.push("compressed".to_string());
-            }
-            if disk_json["SupportsFileBasedCompression"]
-                .as_bool()
-                .unwrap_or(false)
-            {
-                options.push("supports_compression".to_string());
-            }
-
-            // Map Windows drive types to descriptions
-            let drive_type_desc = match drive_type {
-                0 => "Unknown",
-                1 => "No Root Directory",
-                2 => "Removable Disk",
-                3 => "Local Disk",
-                4 => "Network Drive",
-                5 => "Compact Disc",
-                6 => "RAM Disk",
-                _ => "Other",
-            };
-            options.push(format!("type={drive_type_desc}"));
-
-            let used_bytes = if let (Some(size), Some(free)) = (size, free_space) {
-                Some(size - free)
-            } else {
-                None
-            };
-
-            let use_percentage = if let (Some(size), Some(used)) = (size, used_bytes) {
-                if size > 0 {
-                    Some((used as f32 / size as f32) * 100.0)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            mounts.push(MountInfo {
-                device: device_id.clone(),
-                mount_point: device_id,
-                filesystem,
-                options,
-                size_bytes: size,
-                used_bytes,
-                available_bytes: free_space,
-                use_percentage,
-                ..Default::default()
-            });
-        }
-
-        Ok(mounts)
-    }
-
-    fn query_powershell_volumes() -> Result<Vec<Value>> {
-        let output = Command::new("powershell")
-            .args([
-                "-NoProfile",
-                "-Command",
-                r#"
-                Get-Volume | ForEach-Object {
-                    [PSCustomObject]@{
-                        DriveLetter = $_.DriveLetter
-                        FileSystemLabel = $_.FileSystemLabel
-                        FileSystem = $_.FileSystem
-                        HealthStatus = $_.HealthStatus
-                        OperationalStatus = $_.OperationalStatus
-                        SizeRemaining = $_.SizeRemaining
-                        Size = $_.Size
-                        UniqueId = $_.UniqueId
-                        AllocationUnitSize = $_.AllocationUnitSize
-                    }
-                } | ConvertTo-Json -Depth 2
-                "#,
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            return Ok(Vec::new()); // Not critical if this fails
-        }
-
-        let json_str = String::from_utf8(output.stdout)?;
-        if json_str.trim().is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let volumes_json: Value =
-            serde_json::from_str(&json_str).context("Failed to parse PowerShell volume JSON")?;
-
-        let volumes = if volumes_json.is_array() {
-            volumes_json.as_array().unwrap().clone()
-        } else {
-            vec![volumes_json]
-        };
-
-        Ok(volumes)
-    }
-
-    fn enhance_mounts_with_volumes(mounts: &mut [MountInfo], volumes: Vec<Value>) {
-        for mount in mounts.iter_mut() {
-            let drive_letter = mount.device.trim_end_matches(':');
-
-            for volume in &volumes {
-                if let Some(vol_letter) = volume["DriveLetter"].as_str() {
-                    if vol_letter == drive_letter {
-                        // Enhance mount info with volume data
-                        if let Some(label) = volume["FileSystemLabel"].as_str() {
-                            if !label.is_empty() {
-                                mount.options.push(format!("label={label}"));
-                            }
-                        }
-                        if let Some(health) = volume["HealthStatus"].as_str() {
-                            mount.options.push(format!("health={health}"));
-                        }
-                        if let Some(status) = volume["OperationalStatus"].as_str() {
-                            mount.options.push(format!("status={status}"));
-                        }
-                        if let Some(unique_id) = volume["UniqueId"].as_str() {
-                            mount.mount_id = Some(unique_id.to_string());
-                        }
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    fn query_network_drives() -> Result<Vec<MountInfo>> {
-        let output = Command::new("net")
-            .args(["use"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            return Ok(Vec::new()); // Not critical
-        }
-
-        let output_str = String::from_utf8(output.stdout)?;
-        let mut network_mounts = Vec::new();
-
-        // Parse 'net use' output
-        for line in output_str.lines() {
-            if line.contains("\\\\")
-                && (line.contains("Microsoft Windows Network") || line.contains("OK"))
-            {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    let drive_letter = parts[1];
-                    let network_path = parts[2];
-
-                    network_mounts.push(MountInfo {
-                        device: network_path.to_string(),
-                        mount_point: drive_letter.to_string(),
-                        filesystem: "cifs".to_string(),
-                        options: vec!["network".to_string(), "remote".to_string()],
-                        ..Default::default()
-                    });
-                }
-            }
-        }
-
-        Ok(network_mounts)
-    }
-
     pub fn mount_filesystem(source: &str, target: &str, config: &MountConfig) -> Result<()> {
         // Windows mounting operations
         if source.starts_with("\\\\") {
@@ -639,234 +406,6 @@ mod windows_impl {
 mod linux_impl {
     use super::*;
 
-    pub fn list_mounts() -> Result<Vec<MountInfo>> {
-        let mut mounts = Vec::new();
-
-        // Primary method: Parse /proc/mounts
-        if let Ok(proc_mounts) = parse_proc_mounts() {
-            mounts.extend(proc_mounts);
-        }
-
-        // Enhance with /proc/self/mountinfo for additional details
-        if let Ok(mountinfo) = parse_proc_mountinfo() {
-            enhance_mounts_with_mountinfo(&mut mounts, mountinfo);
-        }
-
-        // Add filesystem usage information
-        for mount in &mut mounts {
-            if let Ok(usage) = get_filesystem_usage(&mount.mount_point) {
-                mount.size_bytes = usage.0;
-                mount.used_bytes = usage.1;
-                mount.available_bytes = usage.2;
-                mount.use_percentage = usage.3;
-                mount.inode_total = usage.4;
-                mount.inode_used = usage.5;
-                mount.inode_available = usage.6;
-            }
-        }
-
-        // Fallback to findmnt if available
-        if mounts.is_empty() {
-            if let Ok(findmnt_mounts) = use_findmnt() {
-                mounts.extend(findmnt_mounts);
-            }
-        }
-
-        Ok(mounts)
-    }
-
-    fn parse_proc_mounts() -> Result<Vec<MountInfo>> {
-        let content = fs::read_to_string("/proc/mounts")?;
-        let mut mounts = Vec::new();
-
-        for line in content.lines() {
-            if let Some(mount) = parse_mount_line(line) {
-                mounts.push(mount);
-            }
-        }
-
-        Ok(mounts)
-    }
-
-    fn parse_mount_line(line: &str) -> Option<MountInfo> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 6 {
-            let device = parts[0].to_string();
-            let mount_point = parts[1].to_string();
-            let filesystem = parts[2].to_string();
-            let options: Vec<String> = parts[3].split(',').map(|s| s.to_string()).collect();
-            let dump_frequency = parts[4].parse().unwrap_or(0);
-            let pass_number = parts[5].parse().unwrap_or(0);
-
-            Some(MountInfo {
-                device,
-                mount_point,
-                filesystem,
-                options,
-                dump_frequency,
-                pass_number,
-                ..Default::default()
-            })
-        } else {
-            None
-        }
-    }
-
-    fn parse_proc_mountinfo() -> Result<HashMap<String, MountInfo>> {
-        let content = fs::read_to_string("/proc/self/mountinfo")?;
-        let mut mountinfo = HashMap::new();
-
-        for line in content.lines() {
-            if let Some((mount_point, info)) = parse_mountinfo_line(line) {
-                mountinfo.insert(mount_point, info);
-            }
-        }
-
-        Ok(mountinfo)
-    }
-
-    fn parse_mountinfo_line(line: &str) -> Option<(String, MountInfo)> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 10 {
-            let mount_id = parts[0].to_string();
-            let parent_id = parts[1].to_string();
-            let major_minor = parts[2].to_string();
-            let root = parts[3].to_string();
-            let mount_point = parts[4].to_string();
-            let mount_options: Vec<String> = parts[5].split(',').map(|s| s.to_string()).collect();
-
-            // Find the separator "-"
-            let separator_pos = parts.iter().position(|&x| x == "-")?;
-            if separator_pos + 3 < parts.len() {
-                let filesystem = parts[separator_pos + 1].to_string();
-                let mount_source = parts[separator_pos + 2].to_string();
-
-                let info = MountInfo {
-                    device: mount_source.clone(),
-                    mount_point: mount_point.clone(),
-                    filesystem,
-                    options: mount_options,
-                    mount_id: Some(mount_id),
-                    parent_id: Some(parent_id),
-                    major_minor: Some(major_minor),
-                    root: Some(root),
-                    mount_source: Some(mount_source),
-                    ..Default::default()
-                };
-
-                return Some((mount_point, info));
-            }
-        }
-        None
-    }
-
-    fn enhance_mounts_with_mountinfo(
-        mounts: &mut Vec<MountInfo>,
-        mountinfo: HashMap<String, MountInfo>,
-    ) {
-        for mount in mounts.iter_mut() {
-            if let Some(info) = mountinfo.get(&mount.mount_point) {
-                mount.mount_id = info.mount_id.clone();
-                mount.parent_id = info.parent_id.clone();
-                mount.major_minor = info.major_minor.clone();
-                mount.root = info.root.clone();
-                mount.mount_source = info.mount_source.clone();
-            }
-        }
-    }
-
-    fn get_filesystem_usage(
-        mount_point: &str,
-    ) -> Result<(
-        Option<u64>,
-        Option<u64>,
-        Option<u64>,
-        Option<f32>,
-        Option<u64>,
-        Option<u64>,
-        Option<u64>,
-    )> {
-        use std::ffi::CString;
-        use std::mem;
-
-        // Use libc statvfs for filesystem statistics
-        let path = CString::new(mount_point)?;
-        let mut statvfs: libc::statvfs = unsafe { mem::zeroed() };
-
-        let result = unsafe { libc::statvfs(path.as_ptr(), &mut statvfs) };
-
-        if result == 0 {
-            let block_size = statvfs.f_frsize as u64;
-            let total_blocks = statvfs.f_blocks;
-            let free_blocks = statvfs.f_bavail;
-            let used_blocks = total_blocks - statvfs.f_bfree;
-
-            let total_bytes = total_blocks * block_size;
-            let used_bytes = used_blocks * block_size;
-            let available_bytes = free_blocks * block_size;
-
-            let use_percentage = if total_blocks > 0 {
-                Some((used_blocks as f32 / total_blocks as f32) * 100.0)
-            } else {
-                None
-            };
-
-            let inode_total = Some(statvfs.f_files);
-            let inode_available = Some(statvfs.f_favail);
-            let inode_used = Some(statvfs.f_files - statvfs.f_ffree);
-
-            Ok((
-                Some(total_bytes),
-                Some(used_bytes),
-                Some(available_bytes),
-                use_percentage,
-                inode_total,
-                inode_used,
-                inode_available,
-            ))
-        } else {
-            Ok((None, None, None, None, None, None, None))
-        }
-    }
-
-    fn use_findmnt() -> Result<Vec<MountInfo>> {
-        let output = Command::new("findmnt")
-            .args(&["-J", "-l", "-o", "TARGET,SOURCE,FSTYPE,OPTIONS"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            bail!("findmnt command failed");
-        }
-
-        let json_str = String::from_utf8(output.stdout)?;
-        let findmnt_json: Value =
-            serde_json::from_str(&json_str).context("Failed to parse findmnt JSON")?;
-
-        let mut mounts = Vec::new();
-
-        if let Some(filesystems) = findmnt_json["filesystems"].as_array() {
-            for fs in filesystems {
-                let device = fs["source"].as_str().unwrap_or("").to_string();
-                let mount_point = fs["target"].as_str().unwrap_or("").to_string();
-                let filesystem = fs["fstype"].as_str().unwrap_or("unknown").to_string();
-                let options_str = fs["options"].as_str().unwrap_or("");
-                let options: Vec<String> = options_str.split(',').map(|s| s.to_string()).collect();
-
-                mounts.push(MountInfo {
-                    device,
-                    mount_point,
-                    filesystem,
-                    options,
-                    ..Default::default()
-                });
-            }
-        }
-
-        Ok(mounts)
-    }
-
     pub fn mount_filesystem(source: &str, target: &str, config: &MountConfig) -> Result<()> {
         use std::ffi::CString;
 
@@ -896,7 +435,7 @@ mod linux_impl {
             .transpose()?;
 
         // Prepare mount flags
-        let mut flags = 0u32;
+        let mut flags: libc::c_ulong = 0;
         let mut data_parts = Vec::new();
 
         for option in &config.options {
@@ -923,12 +462,13 @@ mod linux_impl {
             flags |= libc::MS_REMOUNT;
         }
 
-        let data = if data_parts.is_empty() {
-            std::ptr::null()
+        // The option string has to outlive the call
+        let data_c = if data_parts.is_empty() {
+            None
         } else {
-            let data_str = data_parts.join(",");
-            CString::new(data_str)?.as_ptr()
+            Some(CString::new(data_parts.join(","))?)
         };
+        let data = data_c.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
 
         // Perform the mount operation
         let result = unsafe {
@@ -936,7 +476,7 @@ mod linux_impl {
                 source_c.as_ptr(),
                 target_c.as_ptr(),
                 fs_type_c.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
-                flags as libc::c_ulong,
+                flags,
                 data as *const libc::c_void,
             )
         };
@@ -997,127 +537,6 @@ mod linux_impl {
 mod macos_impl {
     use super::*;
 
-    pub fn list_mounts() -> Result<Vec<MountInfo>> {
-        let mut mounts = Vec::new();
-
-        // Use mount command for basic mount information
-        if let Ok(mount_output) = use_mount_command() {
-            mounts.extend(mount_output);
-        }
-
-        // Enhance with diskutil information
-        for mount in &mut mounts {
-            if let Ok(usage) = get_diskutil_usage(&mount.mount_point) {
-                mount.size_bytes = usage.0;
-                mount.used_bytes = usage.1;
-                mount.available_bytes = usage.2;
-                mount.use_percentage = usage.3;
-            }
-        }
-
-        Ok(mounts)
-    }
-
-    fn use_mount_command() -> Result<Vec<MountInfo>> {
-        let output = Command::new("mount")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            bail!("mount command failed");
-        }
-
-        let output_str = String::from_utf8(output.stdout)?;
-        let mut mounts = Vec::new();
-
-        for line in output_str.lines() {
-            if let Some(mount) = parse_macos_mount_line(line) {
-                mounts.push(mount);
-            }
-        }
-
-        Ok(mounts)
-    }
-
-    fn parse_macos_mount_line(line: &str) -> Option<MountInfo> {
-        // Parse lines like: "/dev/disk1s1 on / (apfs, local, read-only, journaled)"
-        if let Some(on_pos) = line.find(" on ") {
-            let device = line[..on_pos].to_string();
-            let rest = &line[on_pos + 4..];
-
-            if let Some(open_paren) = rest.find(" (") {
-                let mount_point = rest[..open_paren].to_string();
-                let options_part = &rest[open_paren + 2..];
-
-                if let Some(close_paren) = options_part.rfind(')') {
-                    let options_str = &options_part[..close_paren];
-                    let options: Vec<String> =
-                        options_str.split(", ").map(|s| s.to_string()).collect();
-
-                    let filesystem = options
-                        .first()
-                        .cloned()
-                        .unwrap_or_else(|| "unknown".to_string());
-
-                    return Some(MountInfo {
-                        device,
-                        mount_point,
-                        filesystem,
-                        options,
-                        ..Default::default()
-                    });
-                }
-            }
-        }
-        None
-    }
-
-    fn get_diskutil_usage(
-        mount_point: &str,
-    ) -> Result<(Option<u64>, Option<u64>, Option<u64>, Option<f32>)> {
-        let output = Command::new("df")
-            .args(&["-k", mount_point])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if !output.status.success() {
-            return Ok((None, None, None, None));
-        }
-
-        let output_str = String::from_utf8(output.stdout)?;
-        let lines: Vec<&str> = output_str.lines().collect();
-
-        if lines.len() >= 2 {
-            let parts: Vec<&str> = lines[1].split_whitespace().collect();
-            if parts.len() >= 4 {
-                let total_kb: u64 = parts[1].parse().unwrap_or(0);
-                let used_kb: u64 = parts[2].parse().unwrap_or(0);
-                let available_kb: u64 = parts[3].parse().unwrap_or(0);
-
-                let total_bytes = total_kb * 1024;
-                let used_bytes = used_kb * 1024;
-                let available_bytes = available_kb * 1024;
-
-                let use_percentage = if total_kb > 0 {
-                    Some((used_kb as f32 / total_kb as f32) * 100.0)
-                } else {
-                    None
-                };
-
-                return Ok((
-                    Some(total_bytes),
-                    Some(used_bytes),
-                    Some(available_bytes),
-                    use_percentage,
-                ));
-            }
-        }
-
-        Ok((None, None, None, None))
-    }
-
     pub fn mount_filesystem(source: &str, target: &str, config: &MountConfig) -> Result<()> {
         if config.dry_run {
             println!("Would mount '{}' on '{}'", source, target);
@@ -1174,28 +593,57 @@ mod macos_impl {
     }
 }
 
-/// Cross-platform mount listing
+/// Cross-platform mount listing, without the space used on each
 pub fn list_mounts() -> Result<Vec<MountInfo>> {
-    #[cfg(target_os = "windows")]
-    return windows_impl::list_mounts();
+    let mounts = nxsh_hal::mounts::list_mounts().map_err(|e| anyhow!("{e}"))?;
+    Ok(mounts.into_iter().map(MountInfo::from).collect())
+}
 
-    #[cfg(target_os = "linux")]
-    return linux_impl::list_mounts();
+impl From<MountEntry> for MountInfo {
+    fn from(mount: MountEntry) -> Self {
+        MountInfo {
+            device: mount.source.clone(),
+            mount_point: mount.mount_point.display().to_string(),
+            filesystem: mount.fs_type,
+            options: mount.options,
+            major_minor: mount
+                .device_number
+                .map(|(major, minor)| format!("{major}:{minor}")),
+            mount_source: Some(mount.source),
+            uuid: mount.uuid,
+            label: mount.label,
+            ..Default::default()
+        }
+    }
+}
 
-    #[cfg(target_os = "macos")]
-    return macos_impl::list_mounts();
+/// Fill in the size, free space and inodes of a mount; stat-ing every
+/// mount could hang on an unreachable network share, so only the outputs
+/// that show them ask
+fn add_usage(mount: &mut MountInfo) {
+    let Ok(usage) = fs_usage(&mount.mount_point) else {
+        return;
+    };
+    mount.size_bytes = Some(usage.total);
+    mount.used_bytes = Some(usage.used());
+    mount.available_bytes = Some(usage.available);
+    mount.use_percentage = usage.use_percent().map(|percent| percent as f32);
+    mount.inode_total = usage.files;
+    mount.inode_available = usage.files_free;
+    mount.inode_used = usage
+        .files
+        .zip(usage.files_free)
+        .map(|(files, free)| files.saturating_sub(free));
+}
 
-    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
-    {
-        // Fallback for unsupported platforms
-        Ok(vec![MountInfo {
-            device: "unsupported".to_string(),
-            mount_point: "/".to_string(),
-            filesystem: "unknown".to_string(),
-            options: vec!["unsupported".to_string()],
-            ..Default::default()
-        }])
+/// The mounts `args` select with `-a` and `-t`, for the structured `mount`
+pub(crate) fn selected_mounts(args: &[String]) -> Result<Vec<MountInfo>, String> {
+    let config = MountConfig::parse_args(args).map_err(|e| e.to_string())?;
+    if config.source.is_some() {
+        return Err("a structured mount only lists the mounts".to_string());
     }
+    let mounts = list_mounts().map_err(|e| e.to_string())?;
+    Ok(filter_mounts(mounts, &config))
 }
 
 /// Cross-platform mount operation
@@ -1273,49 +721,67 @@ fn format_bytes(bytes: u64) -> String {
     }
 }
 
-/// Output mounts in various formats
-fn output_mounts(mounts: &[MountInfo], config: &MountConfig) -> Result<()> {
+/// The listing in the format the options ask for, with the space used on
+/// each mount when that is shown
+fn render_mounts(mounts: &mut [MountInfo], config: &MountConfig) -> Result<String> {
+    if config.json_output || config.verbose {
+        mounts.iter_mut().for_each(add_usage);
+    }
     if config.json_output {
-        output_json(mounts)?;
+        render_json(mounts)
     } else if config.verbose {
-        output_verbose(mounts)?;
+        Ok(render_verbose(mounts))
     } else {
-        output_standard(mounts)?;
+        Ok(render_standard(mounts, config))
     }
-
-    Ok(())
 }
 
-/// Output mounts in standard format
-fn output_standard(mounts: &[MountInfo]) -> Result<()> {
+/// Mounts in the standard format, with their labels and UUIDs when asked
+fn render_standard(mounts: &[MountInfo], config: &MountConfig) -> String {
+    let mut out = String::new();
     for mount in mounts {
-        println!("{}", mount.format_mount_entry());
+        out.push_str(&mount.format_mount_entry());
+        if let Some(label) = mount.label.as_ref().filter(|_| config.show_labels) {
+            out.push_str(&format!(" [{label}]"));
+        }
+        if let Some(uuid) = mount.uuid.as_ref().filter(|_| config.show_uuid) {
+            out.push_str(&format!(" [UUID={uuid}]"));
+        }
+        out.push('\n');
     }
-    Ok(())
+    out
 }
 
-/// Output mounts with verbose information
-fn output_verbose(mounts: &[MountInfo]) -> Result<()> {
+/// Mounts with verbose information
+fn render_verbose(mounts: &[MountInfo]) -> String {
+    let mut out = String::new();
     for mount in mounts {
-        println!("{}", mount.format_mount_with_usage());
+        out.push_str(&mount.format_mount_with_usage());
+        out.push('\n');
 
         if let Some(ref mount_id) = mount.mount_id {
-            println!("  Mount ID: {mount_id}");
+            out.push_str(&format!("  Mount ID: {mount_id}\n"));
         }
         if let Some(ref parent_id) = mount.parent_id {
-            println!("  Parent ID: {parent_id}");
+            out.push_str(&format!("  Parent ID: {parent_id}\n"));
         }
         if let Some(ref major_minor) = mount.major_minor {
-            println!("  Device: {major_minor}");
+            out.push_str(&format!("  Device: {major_minor}\n"));
+        }
+        if let Some(ref uuid) = mount.uuid {
+            out.push_str(&format!("  UUID: {uuid}\n"));
+        }
+        if let Some(ref label) = mount.label {
+            out.push_str(&format!("  Label: {label}\n"));
         }
 
-        println!();
+        out.push('\n');
     }
-    Ok(())
+    out
 }
 
-/// Output mounts in JSON format
-fn output_json(mounts: &[MountInfo]) -> Result<()> {
+/// Mounts in JSON format
+fn render_json(mounts: &[MountInfo]) -> Result<String> {
     let json_mounts: Vec<Value> = mounts
         .iter()
         .map(|mount| {
@@ -1338,14 +804,15 @@ fn output_json(mounts: &[MountInfo]) -> Result<()> {
                 "major_minor": mount.major_minor,
                 "root": mount.root,
                 "mount_source": mount.mount_source,
+                "uuid": mount.uuid,
+                "label": mount.label,
                 "read_only": mount.is_read_only(),
                 "special_filesystem": mount.is_special_filesystem()
             })
         })
         .collect();
 
-    println!("{}", serde_json::to_string_pretty(&json_mounts)?);
-    Ok(())
+    Ok(format!("{}\n", serde_json::to_string_pretty(&json_mounts)?))
 }
 
 /// Display help information
@@ -1398,6 +865,69 @@ fn show_version() {
     println!("Pure Rust implementation with platform-specific optimizations");
 }
 
+/// The `mount` builtin command implementation
+pub struct MountCommand;
+
+impl Builtin for MountCommand {
+    fn name(&self) -> &'static str {
+        "mount"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "List or mount file systems"
+    }
+
+    fn description(&self) -> &'static str {
+        "List the mounted file systems with their types, options and devices, or mount a \
+         device or share on a directory."
+    }
+
+    fn usage(&self) -> &'static str {
+        "mount [-a] [-l] [-u] [-v] [-j] [-t TYPES] | mount [-r] [-o OPTIONS] [--bind] SOURCE DIR"
+    }
+
+    fn help(&self) -> &'static str {
+        "List mounts with 'mount -t ext4,xfs', add usage with 'mount -v' or labels with \
+         'mount -l', or use 'mount | where type == tmpfs' in a structured pipeline."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let (stdout, stderr, status) = run(args);
+        Ok(ExecutionResult::success(status)
+            .with_output(stdout.into_bytes())
+            .with_error(stderr.into_bytes()))
+    }
+}
+
+/// Run mount for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let (stdout, stderr, status) = run(args);
+    io::stdout().write_all(stdout.as_bytes())?;
+    io::stderr().write_all(stderr.as_bytes())?;
+    Ok(status)
+}
+
+/// A listing comes back as text; help, version and mounting itself go
+/// through [`mount_cli`], which prints as it goes
+fn run(args: &[String]) -> (String, String, i32) {
+    let listing = MountConfig::parse_args(args).and_then(|config| {
+        if config.help || config.version || config.source.is_some() {
+            return mount_cli(args).map(|()| String::new());
+        }
+        let mut mounts = filter_mounts(list_mounts()?, &config);
+        render_mounts(&mut mounts, &config)
+    });
+    match listing {
+        Ok(stdout) => (stdout, String::new(), 0),
+        Err(e) => {
+            let message = e.to_string();
+            let message = message.strip_prefix("mount: ").unwrap_or(&message);
+            (String::new(), format!("mount: {message}\n"), 1)
+        }
+    }
+}
+
 /// Main mount CLI entry point
 pub fn mount_cli(args: &[String]) -> Result<()> {
     let config = MountConfig::parse_args(args)?;
@@ -1416,8 +946,8 @@ pub fn mount_cli(args: &[String]) -> Result<()> {
     if config.source.is_none() && config.target.is_none() {
         let mounts = list_mounts().context("Failed to list mounted filesystems")?;
 
-        let filtered_mounts = filter_mounts(mounts, &config);
-        output_mounts(&filtered_mounts, &config)?;
+        let mut filtered_mounts = filter_mounts(mounts, &config);
+        print!("{}", render_mounts(&mut filtered_mounts, &config)?);
         return Ok(());
     }
 
@@ -1437,6 +967,7 @@ pub fn mount_cli(args: &[String]) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
 
     #[test]
     fn test_config_parsing() {
@@ -1563,35 +1094,31 @@ mod tests {
         assert!(MountConfig::parse_args(&args).is_err());
     }
 
-    #[cfg(target_os = "linux")]
     #[test]
-    fn test_linux_mount_line_parsing() {
-        use linux_impl::parse_mount_line;
-
-        let line = "/dev/sda1 / ext4 rw,relatime 0 1";
-        let mount = parse_mount_line(line).unwrap();
-
+    fn test_mount_entry_conversion() {
+        let entry = MountEntry {
+            source: "/dev/sda1".to_string(),
+            mount_point: PathBuf::from("/home"),
+            fs_type: "ext4".to_string(),
+            options: vec!["rw".to_string(), "relatime".to_string()],
+            device_number: Some((8, 1)),
+            uuid: Some("0a1b2c3d".to_string()),
+            label: Some("home".to_string()),
+        };
+        let mount = MountInfo::from(entry);
         assert_eq!(mount.device, "/dev/sda1");
-        assert_eq!(mount.mount_point, "/");
-        assert_eq!(mount.filesystem, "ext4");
-        assert_eq!(mount.options, vec!["rw", "relatime"]);
-        assert_eq!(mount.dump_frequency, 0);
-        assert_eq!(mount.pass_number, 1);
-    }
-
-    #[cfg(target_os = "macos")]
-    #[test]
-    fn test_macos_mount_line_parsing() {
-        use macos_impl::parse_macos_mount_line;
+        assert_eq!(mount.mount_point, "/home");
+        assert_eq!(mount.major_minor.as_deref(), Some("8:1"));
 
-        let line = "/dev/disk1s1 on / (apfs, local, read-only, journaled)";
-        let mount = parse_macos_mount_line(line).unwrap();
-
-        assert_eq!(mount.device, "/dev/disk1s1");
-        assert_eq!(mount.mount_point, "/");
-        assert_eq!(mount.filesystem, "apfs");
-        assert!(mount.options.contains(&"local".to_string()));
-        assert!(mount.options.contains(&"read-only".to_string()));
+        let config = MountConfig {
+            show_labels: true,
+            show_uuid: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_standard(&[mount], &config),
+            "/dev/sda1 on /home type ext4 (rw,relatime) [home] [UUID=0a1b2c3d]\n"
+        );
     }
 
     #[test]
@@ -15,23 +15,29 @@
 //!   --preserve=ATTR_LIST       - Preserve specified attributes
 //!   --no-preserve=ATTR_LIST    - Don't preserve specified attributes
 //!   -Z, --context              - Set SELinux security context
+//!   --progress                 - Show the progress of copies across file systems
 //!   --help                     - Display help and exit
 //!   --version                  - Output version information and exit
-
+//!
+//! `-i` asks through [`Prompter`], so `NXSH_ASSUME` answers it in scripts,
+//! and backups are named as `cp` names them. A move across file systems
+//! copies through `nxsh_hal::copy`, as `cp` does, and shows its progress
+//! under `--progress` or on a terminal when it is large.
+
+use crate::common::hal_message;
+use crate::safety::{backup_path, Clobber, Overwrite, Prompter};
+use crate::transfer::{self, Transfer};
 use crate::ui_design::TableFormatter;
 use anyhow::{anyhow, Result};
-use nxsh_ui::ProgressBar;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::copy::{Reflink, Sparse};
 use std::fs::{self};
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum BackupMode {
-    None,
-    Numbered,
-    Simple,
-    Auto,
-}
+pub use crate::safety::BackupMode;
 
 #[derive(Debug, Clone)]
 pub struct MvOptions {
@@ -48,6 +54,8 @@ pub struct MvOptions {
     pub preserve_attributes: Vec<String>,
     pub no_preserve_attributes: Vec<String>,
     pub context: Option<String>,
+    /// Show the progress of copies across file systems
+    pub progress: bool,
 }
 
 impl Default for MvOptions {
@@ -59,13 +67,33 @@ impl Default for MvOptions {
             update: false,
             verbose: false,
             backup: BackupMode::None,
-            backup_suffix: "~".to_string(),
+            backup_suffix: crate::safety::default_suffix(),
             target_directory: None,
             no_target_directory: false,
             strip_trailing_slashes: false,
             preserve_attributes: Vec::new(),
             no_preserve_attributes: Vec::new(),
             context: None,
+            progress: false,
+        }
+    }
+}
+
+impl MvOptions {
+    /// What to do about destinations that exist
+    fn overwrite(&self) -> Overwrite {
+        let clobber = if self.no_clobber {
+            Clobber::Keep
+        } else if self.interactive && !self.force {
+            Clobber::Ask
+        } else {
+            Clobber::Replace
+        };
+        Overwrite {
+            clobber,
+            update: self.update,
+            backup: self.backup,
+            suffix: self.backup_suffix.clone(),
         }
     }
 }
@@ -75,6 +103,11 @@ pub struct MvCommand {
     sources: Vec<PathBuf>,
     destination: PathBuf,
     statistics: MoveStatistics,
+    /// What relative operands are relative to; empty for the process's
+    /// working directory
+    cwd: PathBuf,
+    stdout: String,
+    stderr: String,
 }
 
 #[derive(Debug, Default)]
@@ -99,6 +132,9 @@ impl MvCommand {
             sources: Vec::new(),
             destination: PathBuf::new(),
             statistics: MoveStatistics::default(),
+            cwd: PathBuf::new(),
+            stdout: String::new(),
+            stderr: String::new(),
         }
     }
 
@@ -109,16 +145,19 @@ impl MvCommand {
 
         let mut i = 0;
         let mut positional_args = Vec::new();
+        let mut suffix_given = false;
 
         while i < args.len() {
             match args[i].as_str() {
                 "-f" | "--force" => {
                     self.options.force = true;
                     self.options.interactive = false;
+                    self.options.no_clobber = false;
                 }
                 "-i" | "--interactive" => {
                     self.options.interactive = true;
                     self.options.force = false;
+                    self.options.no_clobber = false;
                 }
                 "-n" | "--no-clobber" => {
                     self.options.no_clobber = true;
@@ -130,16 +169,12 @@ impl MvCommand {
                     self.options.verbose = true;
                 }
                 "-b" | "--backup" => {
-                    self.options.backup = BackupMode::Auto;
-                }
-                "--backup=numbered" => {
-                    self.options.backup = BackupMode::Numbered;
+                    self.options.backup = BackupMode::from_env().map_err(|e| anyhow!("mv: {e}"))?;
                 }
-                "--backup=simple" => {
-                    self.options.backup = BackupMode::Simple;
-                }
-                "--backup=auto" => {
-                    self.options.backup = BackupMode::Auto;
+                arg if arg.starts_with("--backup=") => {
+                    let control = arg.strip_prefix("--backup=").unwrap();
+                    self.options.backup =
+                        BackupMode::parse(control).map_err(|e| anyhow!("mv: {e}"))?;
                 }
                 "-S" | "--suffix" => {
                     if i + 1 >= args.len() {
@@ -147,6 +182,11 @@ impl MvCommand {
                     }
                     i += 1;
                     self.options.backup_suffix = args[i].clone();
+                    suffix_given = true;
+                }
+                arg if arg.starts_with("--suffix=") => {
+                    self.options.backup_suffix = arg.strip_prefix("--suffix=").unwrap().to_string();
+                    suffix_given = true;
                 }
                 "-t" | "--target-directory" => {
                     if i + 1 >= args.len() {
@@ -161,6 +201,9 @@ impl MvCommand {
                 "--strip-trailing-slashes" => {
                     self.options.strip_trailing_slashes = true;
                 }
+                "--progress" => {
+                    self.options.progress = true;
+                }
                 "--preserve" => {
                     if i + 1 >= args.len() {
                         return Err(anyhow!("mv: option '{}' requires an argument", args[i]));
@@ -200,15 +243,20 @@ impl MvCommand {
                                 'f' => {
                                     self.options.force = true;
                                     self.options.interactive = false;
+                                    self.options.no_clobber = false;
                                 }
                                 'i' => {
                                     self.options.interactive = true;
                                     self.options.force = false;
+                                    self.options.no_clobber = false;
                                 }
                                 'n' => self.options.no_clobber = true,
                                 'u' => self.options.update = true,
                                 'v' => self.options.verbose = true,
-                                'b' => self.options.backup = BackupMode::Auto,
+                                'b' => {
+                                    self.options.backup =
+                                        BackupMode::from_env().map_err(|e| anyhow!("mv: {e}"))?
+                                }
                                 'T' => self.options.no_target_directory = true,
                                 _ => return Err(anyhow!("mv: invalid option -- '{}'", c)),
                             }
@@ -227,6 +275,10 @@ impl MvCommand {
         if positional_args.is_empty() {
             return Err(anyhow!("mv: missing file operand"));
         }
+        // A suffix alone asks for simple backups, as with GNU mv
+        if suffix_given && self.options.backup == BackupMode::None {
+            self.options.backup = BackupMode::Simple;
+        }
 
         // Handle target directory option
         if let Some(ref target_dir) = self.options.target_directory {
@@ -257,54 +309,57 @@ impl MvCommand {
         Ok(())
     }
 
+    /// Move the sources, asking on the process's own terminal or standard
+    /// input, and print what was done
     pub fn execute(&mut self) -> Result<()> {
+        let assume = std::env::var("NXSH_ASSUME").ok();
+        let terminal = io::stdin().is_terminal();
+        let mut input = io::stdin().lock();
+        let mut questions = io::stderr();
+        let mut prompter = Prompter::new(
+            "mv",
+            assume.as_deref(),
+            terminal,
+            &mut input,
+            &mut questions,
+        );
+        let result = self.run(&mut prompter);
+        io::stdout().write_all(std::mem::take(&mut self.stdout).as_bytes())?;
+        io::stderr().write_all(std::mem::take(&mut self.stderr).as_bytes())?;
+        result
+    }
+
+    /// Move the sources, collecting what is printed in `stdout` and `stderr`
+    pub(crate) fn run(&mut self, prompter: &mut Prompter<'_>) -> Result<()> {
         // Validate destination
         if self.sources.len() > 1
-            && !self.destination.is_dir()
+            && !self.cwd.join(&self.destination).is_dir()
             && self.options.target_directory.is_none()
             && !self.options.no_target_directory
         {
-            return Err(anyhow!(
+            let message = format!(
                 "mv: target '{}' is not a directory",
                 self.destination.display()
-            ));
+            );
+            self.stderr.push_str(&format!("{message}\n"));
+            return Err(anyhow!(message));
         }
 
-        // Show progress bar for large operations
-        let total_operations = self.sources.len();
-        let mut progress = if total_operations > 5 {
-            Some(ProgressBar::new(total_operations as u64))
-        } else {
-            None
-        };
-
         // Process each source
         for index in 0..self.sources.len() {
             let source_path = self.sources[index].clone();
-            if let Some(ref mut pb) = progress {
-                pb.set_position(index as u64);
-                pb.set_message(format!("Moving {}", source_path.display()));
-            }
-
-            match self.move_single_item(&source_path) {
-                Ok(_) => {
-                    if source_path.is_dir() {
-                        self.statistics.directories_moved += 1;
-                    } else {
-                        self.statistics.files_moved += 1;
-                    }
-                }
+            let is_dir = self.cwd.join(&source_path).is_dir();
+            match self.move_single_item(&source_path, prompter) {
+                Ok(true) if is_dir => self.statistics.directories_moved += 1,
+                Ok(true) => self.statistics.files_moved += 1,
+                Ok(false) => {}
                 Err(e) => {
-                    eprintln!("mv: {e}");
+                    self.stderr.push_str(&format!("mv: {e}\n"));
                     self.statistics.errors += 1;
                 }
             }
         }
 
-        if let Some(ref mut pb) = progress {
-            pb.set_message("Move operation completed".to_string());
-        }
-
         // Show statistics if verbose
         if self.options.verbose {
             self.print_statistics();
@@ -320,73 +375,76 @@ impl MvCommand {
         }
     }
 
-    fn move_single_item(&mut self, source: &Path) -> Result<()> {
-        if !source.exists() {
+    /// Move one source, shown as given; false when the destination was kept
+    fn move_single_item(&mut self, shown: &Path, prompter: &mut Prompter<'_>) -> Result<bool> {
+        let source = &self.cwd.join(shown);
+        if fs::symlink_metadata(source).is_err() {
             return Err(anyhow!(
                 "cannot stat '{}': No such file or directory",
-                source.display()
+                shown.display()
             ));
         }
 
-        // Determine target path
-        let target = if self.destination.is_dir() && !self.options.no_target_directory {
+        // Determine target path, as shown and as used
+        let destination = self.cwd.join(&self.destination);
+        let target_shown = if destination.is_dir() && !self.options.no_target_directory {
             self.destination
-                .join(source.file_name().unwrap_or(source.as_os_str()))
+                .join(shown.file_name().unwrap_or(shown.as_os_str()))
         } else {
             self.destination.clone()
         };
+        let target = self.cwd.join(&target_shown);
 
         // Check for self-move
         if source.canonicalize()? == target.canonicalize().unwrap_or(target.clone()) {
             if self.options.verbose {
-                println!(
-                    "'{}' and '{}' are the same file",
-                    source.display(),
-                    target.display()
-                );
+                self.stdout.push_str(&format!(
+                    "'{}' and '{}' are the same file\n",
+                    shown.display(),
+                    target_shown.display()
+                ));
             }
-            return Ok(());
+            return Ok(false);
         }
 
-        // Check if target exists
-        if target.exists() {
-            if self.options.no_clobber {
-                if self.options.verbose {
-                    println!("not overwriting '{}' (no-clobber mode)", target.display());
-                }
-                return Ok(());
-            }
-
-            if self.options.update {
-                let source_time = source.metadata()?.modified()?;
-                let target_time = target.metadata()?.modified()?;
-                if source_time <= target_time {
-                    if self.options.verbose {
-                        println!(
-                            "not overwriting '{}' (update mode - target is newer)",
-                            target.display()
-                        );
-                    }
-                    return Ok(());
-                }
-            }
-
-            if self.options.interactive && !self.options.force && !self.prompt_overwrite(&target)? {
-                return Ok(());
-            }
-
-            // Create backup if requested
-            if self.options.backup != BackupMode::None {
-                self.create_backup(&target)?;
-                self.statistics.backups_created += 1;
+        // Skip, ask about or back up a target that exists
+        let overwrite = self.options.overwrite();
+        let existing = fs::symlink_metadata(&target).is_ok();
+        // Earlier numbered backups are looked for beside the target itself,
+        // and the name is shown the way the target is
+        let backup = existing
+            .then(|| backup_path(&target, overwrite.backup, &overwrite.suffix))
+            .flatten()
+            .map(|backup| match backup.file_name() {
+                Some(name) => target_shown.with_file_name(name),
+                None => backup,
+            });
+        let shown_target = target_shown.display().to_string();
+        if !overwrite
+            .allow(source, &target, &shown_target, prompter)
+            .map_err(|message| anyhow!(message))?
+        {
+            if self.options.verbose {
+                self.stdout
+                    .push_str(&format!("not overwriting '{shown_target}'\n"));
             }
+            return Ok(false);
+        }
+        if backup.is_some() {
+            self.statistics.backups_created += 1;
         }
 
         // Attempt atomic rename first (works if on same filesystem)
         match fs::rename(source, &target) {
             Ok(_) => {
                 if self.options.verbose {
-                    println!("'{}' -> '{}'", source.display(), target.display());
+                    let backup = backup
+                        .map(|backup| format!(" (backup: '{}')", backup.display()))
+                        .unwrap_or_default();
+                    self.stdout.push_str(&format!(
+                        "'{}' -> '{shown_target}'{backup}\n",
+                        shown.display()
+                    ));
                 }
 
                 // Update statistics
@@ -394,119 +452,97 @@ impl MvCommand {
                     self.statistics.bytes_moved += metadata.len();
                 }
 
-                Ok(())
+                Ok(true)
             }
             Err(_) => {
                 // Rename failed, try copy + remove for cross-filesystem moves
-                self.copy_and_remove(source, &target)
+                self.copy_and_remove(source, &target, shown, &target_shown, prompter)?;
+                Ok(true)
             }
         }
     }
 
-    fn copy_and_remove(&mut self, source: &Path, target: &Path) -> Result<()> {
+    /// Move `source` to another file system by copying it and removing
+    /// it, drawing the progress of the copy when it is shown
+    fn copy_and_remove(
+        &mut self,
+        source: &Path,
+        target: &Path,
+        shown: &Path,
+        target_shown: &Path,
+        prompter: &mut Prompter<'_>,
+    ) -> Result<()> {
+        let mut files = Vec::new();
         if source.is_dir() {
-            self.copy_directory_recursive(source, target)?;
-            fs::remove_dir_all(source)?;
+            collect_files(source, target, &mut files)?;
         } else {
-            fs::copy(source, target)?;
-            fs::remove_file(source)?;
-        }
-
-        if self.options.verbose {
-            println!(
-                "'{}' -> '{}' (copied across filesystems)",
-                source.display(),
-                target.display()
-            );
+            files.push((
+                source.to_path_buf(),
+                target.to_path_buf(),
+                fs::metadata(source)?.len(),
+            ));
         }
-
-        Ok(())
-    }
-
-    #[allow(clippy::only_used_in_recursion)]
-    fn copy_directory_recursive(&self, source: &Path, target: &Path) -> Result<()> {
-        if !target.exists() {
-            fs::create_dir_all(target)?;
+        let total = files.iter().map(|(_, _, size)| size).sum();
+        let show = self.options.progress || (prompter.on_terminal() && total >= transfer::LARGE);
+        let mut drawing = show.then(|| Transfer::new(total));
+        let mut done = 0;
+        let mut drawn: Option<Instant> = None;
+        for (from, to, size) in &files {
+            let name = from
+                .strip_prefix(&self.cwd)
+                .unwrap_or(from)
+                .display()
+                .to_string();
+            let mut file_done = 0;
+            let mut progress = |bytes: u64| {
+                done += bytes;
+                file_done += bytes;
+                if let Some(drawing) = drawing.as_mut() {
+                    if drawn.is_none_or(|at| at.elapsed() >= transfer::REDRAW) {
+                        let line = drawing.line(done, &name, file_done, *size);
+                        let _ = write!(prompter.out(), "{line}");
+                        let _ = prompter.out().flush();
+                        drawn = Some(Instant::now());
+                    }
+                }
+            };
+            nxsh_hal::copy::copy_file(from, to, Reflink::Auto, Sparse::Auto, &mut progress)
+                .map_err(|e| anyhow!("cannot copy '{name}': {}", hal_message(&e)))?;
+            fs::set_permissions(to, fs::metadata(from)?.permissions())?;
         }
-
-        for entry in fs::read_dir(source)? {
-            let entry = entry?;
-            let source_path = entry.path();
-            let target_path = target.join(entry.file_name());
-
-            if source_path.is_dir() {
-                self.copy_directory_recursive(&source_path, &target_path)?;
-            } else {
-                fs::copy(&source_path, &target_path)?;
+        if let Some(drawing) = drawing.as_mut() {
+            if let Some((from, _, size)) = files.last() {
+                let name = from
+                    .strip_prefix(&self.cwd)
+                    .unwrap_or(from)
+                    .display()
+                    .to_string();
+                let line = drawing.line(done, &name, *size, *size);
+                let _ = writeln!(prompter.out(), "{line}");
             }
         }
+        self.statistics.bytes_moved += done;
 
-        Ok(())
-    }
-
-    fn prompt_overwrite(&self, target: &Path) -> Result<bool> {
-        print!("mv: overwrite '{}'? ", target.display());
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-
-        let response = input.trim().to_lowercase();
-        Ok(response.starts_with('y') || response == "yes")
-    }
-
-    fn create_backup(&self, target: &Path) -> Result<()> {
-        let backup_path = match self.options.backup {
-            BackupMode::Numbered => self.create_numbered_backup(target)?,
-            BackupMode::Simple => {
-                let mut backup = target.as_os_str().to_os_string();
-                backup.push(&self.options.backup_suffix);
-                PathBuf::from(backup)
-            }
-            BackupMode::Auto => {
-                // Use numbered if backup already exists, simple otherwise
-                let simple_backup = {
-                    let mut backup = target.as_os_str().to_os_string();
-                    backup.push(&self.options.backup_suffix);
-                    PathBuf::from(backup)
-                };
-
-                if simple_backup.exists() {
-                    self.create_numbered_backup(target)?
-                } else {
-                    simple_backup
-                }
-            }
-            BackupMode::None => return Ok(()),
-        };
-
-        fs::copy(target, &backup_path)?;
+        if source.is_dir() {
+            fs::remove_dir_all(source)?;
+        } else {
+            fs::remove_file(source)?;
+        }
 
         if self.options.verbose {
-            println!(
-                "backup: '{}' -> '{}'",
-                target.display(),
-                backup_path.display()
-            );
+            self.stdout.push_str(&format!(
+                "'{}' -> '{}' (copied across filesystems)\n",
+                shown.display(),
+                target_shown.display()
+            ));
         }
 
         Ok(())
     }
 
-    fn create_numbered_backup(&self, target: &Path) -> Result<PathBuf> {
-        for i in 1..=999 {
-            let backup_path = format!("{}.~{}~", target.display(), i);
-            let backup_path = PathBuf::from(backup_path);
-            if !backup_path.exists() {
-                return Ok(backup_path);
-            }
-        }
-        Err(anyhow!("too many backup files"))
-    }
-
-    fn print_statistics(&self) {
-        println!("Move Operation Statistics");
-        println!("=========================");
+    fn print_statistics(&mut self) {
+        self.stdout.push_str("Move Operation Statistics\n");
+        self.stdout.push_str("=========================\n");
 
         let formatter = TableFormatter::new();
 
@@ -531,7 +567,8 @@ impl MvCommand {
         ];
 
         for row in &data {
-            println!("{:<20} {}", row[0], row[1]);
+            self.stdout
+                .push_str(&format!("{:<20} {}\n", row[0], row[1]));
         }
     }
 
@@ -577,6 +614,33 @@ impl MvCommand {
     }
 }
 
+/// Create the directories of `source` under `target`, and list the files
+/// to copy with their sizes
+fn collect_files(
+    source: &Path,
+    target: &Path,
+    files: &mut Vec<(PathBuf, PathBuf, u64)>,
+) -> Result<()> {
+    if !target.exists() {
+        fs::create_dir_all(target)?;
+    }
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let target_path = target.join(entry.file_name());
+
+        if source_path.is_dir() {
+            collect_files(&source_path, &target_path, files)?;
+        } else {
+            let size = fs::metadata(&source_path)?.len();
+            files.push((source_path, target_path, size));
+        }
+    }
+
+    Ok(())
+}
+
 /// CLI interface for the mv command
 pub fn mv_cli(args: &[String]) -> Result<()> {
     let mut command = MvCommand::new();
@@ -589,12 +653,63 @@ pub fn execute(
     args: &[String],
     _context: &crate::common::BuiltinContext,
 ) -> crate::common::BuiltinResult<i32> {
-    match mv_cli(args) {
-        Ok(_) => Ok(0),
-        Err(e) => {
-            eprintln!("mv: {e}");
-            Ok(1)
+    let mut command = MvCommand::new();
+    if let Err(e) = command.parse_args(args) {
+        eprintln!("{e}");
+        return Ok(1);
+    }
+    // What went wrong is printed as it happens
+    Ok(if command.execute().is_ok() { 0 } else { 1 })
+}
+
+/// `mv` for the shell, named so as not to clash with [`MvCommand`]
+pub struct MvBuiltin;
+
+impl Builtin for MvBuiltin {
+    fn name(&self) -> &'static str {
+        "mv"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Move or rename files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rename SOURCE to DEST, or move SOURCEs into DIRECTORY, asking before, \
+         skipping or backing up files that would be overwritten when told to."
+    }
+
+    fn usage(&self) -> &'static str {
+        "mv [-finuvbT] [--backup[=CONTROL]] [-S SUFFIX] [-t DIRECTORY] SOURCE... DEST"
+    }
+
+    fn help(&self) -> &'static str {
+        "Move or rename files. Use 'mv old new' to rename, 'mv -i a b' to be asked \
+         before overwriting b, or 'mv -u a b' to replace b only when a is newer."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut command = MvCommand::new();
+        command.cwd = ctx.cwd.clone();
+        if let Err(e) = command.parse_args(args) {
+            return Ok(ExecutionResult::success(1).with_error(format!("{e}\n").into_bytes()));
         }
+        let assume = ctx.get_var("NXSH_ASSUME");
+        let mut prompter = Prompter::new(
+            "mv",
+            assume.as_deref(),
+            ctx.interactive,
+            &mut ctx.stdin,
+            &mut ctx.stderr,
+        );
+        let status = if command.run(&mut prompter).is_ok() {
+            0
+        } else {
+            1
+        };
+        Ok(ExecutionResult::success(status)
+            .with_output(command.stdout.into_bytes())
+            .with_error(command.stderr.into_bytes()))
     }
 }
 
@@ -667,9 +782,46 @@ mod tests {
         cmd.sources = vec![source.clone()];
         cmd.destination = dest.clone();
 
-        // Note: This test requires manual interaction, so we just verify the setup
+        // The answer is read from the input when it is not a terminal
+        let mut input = &b"n\n"[..];
+        let mut questions = Vec::new();
+        let mut prompter = Prompter::new("mv", None, false, &mut input, &mut questions);
+        assert!(cmd.run(&mut prompter).is_ok());
+        assert_eq!(
+            String::from_utf8(questions).unwrap(),
+            format!("mv: overwrite '{}'? ", dest.display())
+        );
         assert!(source.exists());
-        assert!(dest.exists());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "dest content");
+    }
+
+    #[test]
+    fn test_mv_numbered_backups_in_cwd() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a"), "first").unwrap();
+        fs::write(temp_dir.path().join("b"), "old").unwrap();
+        fs::write(temp_dir.path().join("b.~1~"), "older").unwrap();
+
+        let mut cmd = MvCommand::new();
+        cmd.cwd = temp_dir.path().to_path_buf();
+        let args: Vec<String> = ["-v", "--backup=numbered", "a", "b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        cmd.parse_args(&args).unwrap();
+        let mut input = &b""[..];
+        let mut questions = Vec::new();
+        let mut prompter = Prompter::new("mv", None, false, &mut input, &mut questions);
+        assert!(cmd.run(&mut prompter).is_ok());
+        assert!(cmd.stdout.starts_with("'a' -> 'b' (backup: 'b.~2~')\n"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("b")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("b.~2~")).unwrap(),
+            "old"
+        );
     }
 
     #[test]
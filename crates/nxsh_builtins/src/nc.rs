@@ -1,54 +1,795 @@
-//! `nc` (netcat) builtin - Network connection utility.
+//! `nc` builtin - read and write TCP and UDP connections
 //!
-//! Delegates to the system `nc` or `netcat` binary when available to provide
-//! complete networking functionality. When the binary is unavailable, falls
-//! back to a basic internal implementation for simple TCP connections.
-
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-
-/// Entry point for the `nc` builtin.
-pub fn nc_cli(args: &[String]) -> Result<()> {
-    // Try common netcat binary names
-    let nc_commands = vec!["nc", "netcat", "ncat"];
-    
-    for nc_cmd in nc_commands {
-        if let Ok(path) = which(nc_cmd) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("nc: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
-        }
-    }
-    
-    // Basic internal fallback
-    if args.len() < 2 {
-        return Err(anyhow!("nc: usage: nc host port"));
-    }
-    
-    let host = &args[0];
-    let port = args[1].parse::<u16>()
-        .map_err(|_| anyhow!("nc: invalid port: {}", args[1]))?;
-    
-    // Simple TCP connection test
-    use std::net::TcpStream;
-    use std::time::Duration;
-    
-    println!("Connecting to {host} port {port}");
-    
-    let addr = format!("{host}:{port}").parse()
-        .map_err(|e| anyhow!("nc: invalid address {}:{}: {}", host, port, e))?;
-    
-    match TcpStream::connect_timeout(&addr, Duration::from_secs(10)) {
-        Ok(_stream) => {
-            println!("Connection to {host} {port} port [tcp/*] succeeded!");
-            Ok(())
-        }
-        Err(e) => {
-            Err(anyhow!("nc: connect to {} port {}: {}", host, port, e))
+//! Syntax:
+//!   nc [-nuvz] [-w SECS] HOST PORT
+//!   nc -z [-nuv] [-w SECS] HOST PORT[-PORT]...
+//!   nc -l [-nuv] [-w SECS] [-p PORT] [ADDRESS] [PORT]
+//!
+//! Connects to PORT on HOST, copies standard input to the connection and
+//! prints what comes back. With `-l` nc instead waits on PORT of ADDRESS,
+//! every IPv4 address by default, for one client and then does the same
+//! with it. With `-u` it uses UDP: the client sends each chunk of input as
+//! a datagram, and the listener talks to whoever sent the first one.
+//!
+//! Once input ends the sending half of a TCP connection is shut, so that the
+//! peer sees the end of its input too, and nc goes on printing until the
+//! peer closes its half. A UDP exchange has no end and lasts until `-w`
+//! passes without data or the shell's time limit.
+//!
+//! With `-z` nothing is sent or printed: nc only tries each PORT, or each
+//! port of a range like 20-25, and exits 0 when any of them took the
+//! connection. A UDP port counts as open unless the host refuses the probe.
+//!
+//! Options:
+//!   -l          listen for a client instead of connecting
+//!   -p PORT     port to listen on, 0 for any free one
+//!   -u          use UDP instead of TCP
+//!   -z          only check whether the ports take a connection
+//!   -v          report connections, and with `-z` the closed ports, on
+//!               standard error
+//!   -w SECS     give up connecting after SECS, and quit once SECS pass
+//!               without data either way
+//!   -n          take HOST as an address, never looking it up
+//!
+//! The exit status is 0 when the connection was made or, with `-z`, when any
+//! port was open, and 1 otherwise or for bad options.
+
+use crate::common::{io_message, seconds, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::network::{self, ConnectionTarget};
+use nxsh_hal::HalError;
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often waits check whether the run should stop
+const TICK: Duration = Duration::from_millis(50);
+
+/// How long a UDP probe waits for a refusal without `-w`
+const UDP_PROBE_WAIT: Duration = Duration::from_secs(1);
+
+/// Bytes moved at a time, and the most one datagram carries
+const CHUNK: usize = 16 * 1024;
+
+/// What the resolver puts before its reason for not finding a name
+const LOOKUP_PREFIX: &str = "failed to lookup address information: ";
+
+/// The `nc` builtin command implementation
+pub struct NcCommand;
+
+impl Builtin for NcCommand {
+    fn name(&self) -> &'static str {
+        "nc"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Read and write TCP and UDP connections"
+    }
+
+    fn description(&self) -> &'static str {
+        "Connect to a TCP or UDP port, or listen on one, and copy standard input to the \
+         connection and what comes back to standard output; or check which ports are open."
+    }
+
+    fn usage(&self) -> &'static str {
+        "nc [-nuvz] [-w SECS] HOST PORT[-PORT]...\n\
+         nc -l [-nuv] [-w SECS] [-p PORT] [ADDRESS] [PORT]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Talk to a TCP or UDP service. Use 'nc -z -w 1 example.com 80' to check a port, \
+         'nc -l -p 8080' to wait for a client or 'nc -u 127.0.0.1 53' for UDP."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(
+                    ExecutionResult::failure(1).with_error(format!("nc: {message}\n").into_bytes())
+                )
+            }
+        };
+        // The input moves to the thread that sends it, leaving the context
+        // free to tell when the shell's time is up
+        let mut input = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+        let terminal = ctx.is_interactive() && io::stdin().is_terminal();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let status = if ctx.is_interactive() && io::stdout().is_terminal() {
+            let mut out = std::mem::replace(&mut ctx.stdout, Box::new(io::sink()));
+            let mut err = std::mem::replace(&mut ctx.stderr, Box::new(io::sink()));
+            let status = run(
+                &options,
+                &mut Streams {
+                    input: &mut *input,
+                    terminal,
+                    output: &mut *out,
+                    errors: &mut *err,
+                    stopped: &|| ctx.is_timed_out(),
+                },
+            );
+            ctx.stdout = out;
+            ctx.stderr = err;
+            status
+        } else {
+            run(
+                &options,
+                &mut Streams {
+                    input: &mut *input,
+                    terminal,
+                    output: &mut stdout,
+                    errors: &mut stderr,
+                    stopped: &|| ctx.is_timed_out(),
+                },
+            )
+        };
+        ctx.stdin = input;
+        Ok(ExecutionResult::success(status)
+            .with_output(stdout)
+            .with_error(stderr))
+    }
+}
+
+/// Run nc for the legacy dispatcher on the process's standard streams
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("nc: {message}");
+            return Ok(1);
+        }
+    };
+    Ok(run(
+        &options,
+        &mut Streams {
+            input: &mut io::stdin(),
+            terminal: io::stdin().is_terminal(),
+            output: &mut io::stdout(),
+            errors: &mut io::stderr(),
+            stopped: &|| false,
+        },
+    ))
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    listen: bool,
+    udp: bool,
+    scan: bool,
+    verbose: bool,
+    numeric: bool,
+    wait: Option<Duration>,
+    /// The host to connect to, or the address to listen on
+    host: Option<String>,
+    /// The ports to connect to, or the one to listen on
+    ports: Vec<u16>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            listen: false,
+            udp: false,
+            scan: false,
+            verbose: false,
+            numeric: false,
+            wait: None,
+            host: None,
+            ports: Vec::new(),
+        };
+        let mut listen_port = None;
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'l' => options.listen = true,
+                            'u' => options.udp = true,
+                            'z' => options.scan = true,
+                            'v' => options.verbose = true,
+                            'n' => options.numeric = true,
+                            'p' | 'w' => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().map(String::as_str).ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached,
+                                };
+                                if flag == 'p' {
+                                    listen_port = Some(port(value, 0)?);
+                                } else {
+                                    let wait = seconds(value)
+                                        .filter(|wait| !wait.is_zero())
+                                        .ok_or_else(|| format!("invalid timeout '{value}'"))?;
+                                    options.wait = Some(wait);
+                                }
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
+
+        if options.listen {
+            if options.scan {
+                return Err("cannot use -z and -l".to_string());
+            }
+            let (host, port_operand) = match (listen_port, operands.as_slice()) {
+                (Some(_), []) => (None, None),
+                (Some(_), [host]) | (None, [host, _]) => (Some(host), operands.get(1)),
+                (None, [port]) => (None, Some(port)),
+                (None, []) => return Err("missing port number".to_string()),
+                (Some(_), [_, extra, ..]) | (None, [_, _, extra, ..]) => {
+                    return Err(format!("extra operand '{extra}'"))
+                }
+            };
+            options.host = host.cloned();
+            let port = match port_operand {
+                Some(text) => port(text, 0)?,
+                None => listen_port.unwrap_or_default(),
+            };
+            options.ports.push(port);
+            return Ok(options);
+        }
+
+        if listen_port.is_some() {
+            return Err("option -p requires -l".to_string());
+        }
+        let (host, ports) = match operands.split_first() {
+            Some((host, ports)) if !ports.is_empty() => (host, ports),
+            Some(_) => return Err("missing port number".to_string()),
+            None => return Err("missing host operand".to_string()),
+        };
+        options.host = Some(host.clone());
+        for text in ports {
+            if !options.scan && !options.ports.is_empty() {
+                return Err(format!("extra operand '{text}'"));
+            }
+            options.ports.extend(port_range(text, options.scan)?);
+        }
+        Ok(options)
+    }
+}
+
+/// A port number of at least `min`
+fn port(text: &str, min: u16) -> Result<u16, String> {
+    match text.parse::<u16>() {
+        Ok(port) if port >= min => Ok(port),
+        _ => Err(format!("invalid port '{text}'")),
+    }
+}
+
+/// A port, or with `-z` a range of them like 20-25
+fn port_range(text: &str, scan: bool) -> Result<std::ops::RangeInclusive<u16>, String> {
+    match text.split_once('-') {
+        Some((first, last)) if scan => {
+            let (first, last) = (port(first, 1), port(last, 1));
+            match (first, last) {
+                (Ok(first), Ok(last)) if first <= last => Ok(first..=last),
+                _ => Err(format!("invalid port range '{text}'")),
+            }
+        }
+        Some(_) => Err(format!("port range '{text}' needs -z")),
+        None => port(text, 1).map(|port| port..=port),
+    }
+}
+
+/// Where a run reads and writes
+struct Streams<'a> {
+    input: &'a mut (dyn Read + Send),
+    /// Whether input comes from a terminal, which is polled so that the
+    /// sender can stop waiting once the connection closes
+    terminal: bool,
+    output: &'a mut dyn Write,
+    errors: &'a mut dyn Write,
+    stopped: &'a dyn Fn() -> bool,
+}
+
+fn run(options: &Options, streams: &mut Streams) -> i32 {
+    let result = if options.scan {
+        scan(options, streams)
+    } else if options.listen {
+        listen(options, streams)
+    } else {
+        connect(options, streams)
+    };
+    match result {
+        Ok(status) => status,
+        Err(message) => {
+            let _ = writeln!(streams.errors, "nc: {message}");
+            1
+        }
+    }
+}
+
+fn protocol(options: &Options) -> &'static str {
+    if options.udp {
+        "udp"
+    } else {
+        "tcp"
+    }
+}
+
+fn connect(options: &Options, streams: &mut Streams) -> Result<i32, String> {
+    let host = options.host.as_deref().unwrap_or_default();
+    let port = options.ports[0];
+    let target = target(host, port, options.numeric)?;
+    let link = if options.udp {
+        network::connect_udp(&target).map(Link::Udp)
+    } else {
+        network::connect_tcp(&target, options.wait).map(Link::Tcp)
+    };
+    let link = link.map_err(|e| failure(&e, protocol(options)))?;
+    if options.verbose {
+        let _ = writeln!(
+            streams.errors,
+            "Connection to {host} {port} port [{}/*] succeeded!",
+            protocol(options)
+        );
+    }
+    relay(&link, options.wait, streams)?;
+    Ok(0)
+}
+
+fn listen(options: &Options, streams: &mut Streams) -> Result<i32, String> {
+    let ip = match &options.host {
+        Some(host) => address(host, options.numeric)?,
+        None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+    let addr = SocketAddr::new(ip, options.ports[0]);
+    let link = if options.udp {
+        let socket = network::bind_udp(addr).map_err(|e| failure(&e, "udp"))?;
+        announce(options, streams, socket.local_addr());
+        socket
+            .set_read_timeout(Some(TICK))
+            .map_err(|e| io_message(&e))?;
+        let mut buf = vec![0; CHUNK];
+        let (n, peer) = loop {
+            if (streams.stopped)() {
+                return Ok(1);
+            }
+            match socket.recv_from(&mut buf) {
+                Ok(received) => break received,
+                Err(e) if waiting(&e) => {}
+                Err(e) => return Err(io_message(&e)),
+            }
+        };
+        socket.connect(peer).map_err(|e| io_message(&e))?;
+        received(options, streams, peer);
+        write(streams.output, &buf[..n])?;
+        Link::Udp(socket)
+    } else {
+        let listener = network::listen_tcp(addr).map_err(|e| failure(&e, "tcp"))?;
+        announce(options, streams, listener.local_addr());
+        listener.set_nonblocking(true).map_err(|e| io_message(&e))?;
+        let (stream, peer) = loop {
+            if (streams.stopped)() {
+                return Ok(1);
+            }
+            match listener.accept() {
+                Ok(accepted) => break accepted,
+                Err(e) if waiting(&e) => thread::sleep(TICK),
+                Err(e) => return Err(io_message(&e)),
+            }
+        };
+        // Some systems hand the listener's mode down to what it accepts
+        stream.set_nonblocking(false).map_err(|e| io_message(&e))?;
+        received(options, streams, peer);
+        Link::Tcp(stream)
+    };
+    relay(&link, options.wait, streams)?;
+    Ok(0)
+}
+
+fn announce(options: &Options, streams: &mut Streams, local: io::Result<SocketAddr>) {
+    if let (true, Ok(local)) = (options.verbose, local) {
+        let _ = writeln!(
+            streams.errors,
+            "Listening on {} {}",
+            local.ip(),
+            local.port()
+        );
+    }
+}
+
+fn received(options: &Options, streams: &mut Streams, peer: SocketAddr) {
+    if options.verbose {
+        let _ = writeln!(
+            streams.errors,
+            "Connection received on {} {}",
+            peer.ip(),
+            peer.port()
+        );
+    }
+}
+
+fn scan(options: &Options, streams: &mut Streams) -> Result<i32, String> {
+    let host = options.host.as_deref().unwrap_or_default();
+    let ip = address(host, options.numeric)?;
+    let mut status = 1;
+    for &port in &options.ports {
+        if (streams.stopped)() {
+            break;
+        }
+        let addr = SocketAddr::new(ip, port);
+        let open = if options.udp {
+            probe_udp(addr, options.wait)
+        } else {
+            network::connect_tcp(&ConnectionTarget::Address(addr), options.wait)
+                .map(drop)
+                .map_err(|e| failure(&e, "tcp"))
+        };
+        match open {
+            Ok(()) => {
+                status = 0;
+                if options.verbose {
+                    let _ = writeln!(
+                        streams.errors,
+                        "Connection to {host} {port} port [{}/*] succeeded!",
+                        protocol(options)
+                    );
+                }
+            }
+            Err(message) => {
+                if options.verbose {
+                    let _ = writeln!(streams.errors, "nc: {message}");
+                }
+            }
         }
     }
+    Ok(status)
 }
 
+/// Send a datagram to `addr` and listen for the host refusing it; silence
+/// counts as open, as nothing more can be told from it
+fn probe_udp(addr: SocketAddr, wait: Option<Duration>) -> Result<(), String> {
+    let socket =
+        network::connect_udp(&ConnectionTarget::Address(addr)).map_err(|e| failure(&e, "udp"))?;
+    let refused = |e: io::Error| {
+        format!(
+            "connect to {} port {} (udp) failed: {}",
+            addr.ip(),
+            addr.port(),
+            io_message(&e)
+        )
+    };
+    socket
+        .set_read_timeout(Some(wait.unwrap_or(UDP_PROBE_WAIT)))
+        .and_then(|()| socket.send(b"X"))
+        .map_err(refused)?;
+    match socket.recv(&mut [0; 512]) {
+        Err(e) if closed(&e) => Err(refused(e)),
+        _ => Ok(()),
+    }
+}
+
+/// HOST and PORT as a target, looked up when connecting unless `-n` was
+/// given
+fn target(host: &str, port: u16, numeric: bool) -> Result<ConnectionTarget, String> {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => Ok(ConnectionTarget::Address(SocketAddr::new(ip, port))),
+        Err(_) if numeric => Err(format!("{host}: Name or service not known")),
+        Err(_) => Ok(ConnectionTarget::Host {
+            host: host.to_string(),
+            port,
+        }),
+    }
+}
+
+/// The first address of `host`
+fn address(host: &str, numeric: bool) -> Result<IpAddr, String> {
+    match target(host, 0, numeric)? {
+        ConnectionTarget::Address(addr) => Ok(addr.ip()),
+        target => target
+            .resolve()
+            .map(|addrs| addrs[0].ip())
+            .map_err(|e| failure(&e, "")),
+    }
+}
+
+/// A HAL failure to look up, bind or connect, the way nc words it
+fn failure(e: &HalError, protocol: &str) -> String {
+    match e {
+        HalError::Network(err) => {
+            let host = err.address.as_deref().unwrap_or_default();
+            if err.operation == "resolve" {
+                let reason = err.message.trim_start_matches(LOOKUP_PREFIX);
+                return format!("{host}: {reason}");
+            }
+            format!(
+                "{} to {host} port {} ({protocol}) failed: {}",
+                err.operation,
+                err.port.unwrap_or_default(),
+                err.message
+            )
+        }
+        other => other.to_string(),
+    }
+}
+
+/// A wait that ran out rather than failed
+fn waiting(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+    )
+}
+
+/// The peer refused or dropped the connection
+fn closed(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+fn write(output: &mut dyn Write, data: &[u8]) -> Result<(), String> {
+    output
+        .write_all(data)
+        .and_then(|()| output.flush())
+        .map_err(|e| io_message(&e))
+}
+
+/// A TCP connection, or a UDP socket talking to one peer
+enum Link {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Link {
+    fn send(&self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Link::Tcp(stream) => {
+                let mut stream: &TcpStream = stream;
+                stream.write_all(data)
+            }
+            Link::Udp(socket) => socket.send(data).map(drop),
+        }
+    }
+
+    fn receive(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Link::Tcp(stream) => {
+                let mut stream: &TcpStream = stream;
+                stream.read(buf)
+            }
+            Link::Udp(socket) => socket.recv(buf),
+        }
+    }
+
+    /// Tell the peer nothing more is coming, which only TCP can
+    fn finish(&self) {
+        if let Link::Tcp(stream) = self {
+            let _ = stream.shutdown(Shutdown::Write);
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Link::Tcp(stream) => stream.set_read_timeout(timeout),
+            Link::Udp(socket) => socket.set_read_timeout(timeout),
+        }
+    }
+}
+
+/// Copy input to the link and what arrives to output, until the peer
+/// closes its half, `wait` passes without data either way or the run is
+/// stopped
+fn relay(link: &Link, wait: Option<Duration>, streams: &mut Streams) -> Result<(), String> {
+    link.set_read_timeout(Some(TICK))
+        .map_err(|e| io_message(&e))?;
+    let done = AtomicBool::new(false);
+    let active = Mutex::new(Instant::now());
+    let input = &mut *streams.input;
+    let terminal = streams.terminal;
+    let (received, sent) = thread::scope(|scope| {
+        let sender = scope.spawn(|| send(input, terminal, link, &done, &active));
+        let received = receive(link, wait, streams.output, streams.stopped, &active);
+        done.store(true, Ordering::Relaxed);
+        (received, sender.join().unwrap_or(Ok(())))
+    });
+    received?;
+    match sent {
+        Err(e) if !closed(&e) => Err(io_message(&e)),
+        _ => Ok(()),
+    }
+}
+
+fn send(
+    input: &mut (dyn Read + Send),
+    terminal: bool,
+    link: &Link,
+    done: &AtomicBool,
+    active: &Mutex<Instant>,
+) -> io::Result<()> {
+    let mut buf = vec![0; CHUNK];
+    loop {
+        if terminal && !readable(done) {
+            return Ok(());
+        }
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if done.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        link.send(&buf[..n])?;
+        *active.lock().unwrap() = Instant::now();
+    }
+    link.finish();
+    Ok(())
+}
+
+fn receive(
+    link: &Link,
+    wait: Option<Duration>,
+    output: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+    active: &Mutex<Instant>,
+) -> Result<(), String> {
+    let mut buf = vec![0; CHUNK];
+    loop {
+        if stopped() {
+            return Ok(());
+        }
+        if let Some(wait) = wait {
+            if active.lock().unwrap().elapsed() >= wait {
+                return Ok(());
+            }
+        }
+        match link.receive(&mut buf) {
+            Ok(0) if matches!(link, Link::Tcp(_)) => return Ok(()),
+            Ok(n) => {
+                write(output, &buf[..n])?;
+                *active.lock().unwrap() = Instant::now();
+            }
+            Err(e) if waiting(&e) => {}
+            Err(e) => return Err(io_message(&e)),
+        }
+    }
+}
+
+/// Wait for the terminal to have input, returning false once `done` is set
+#[cfg(unix)]
+pub(crate) fn readable(done: &AtomicBool) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    while !done.load(Ordering::Relaxed) {
+        // SAFETY: a single valid pollfd
+        match unsafe { libc::poll(&mut fd, 1, TICK.as_millis() as libc::c_int) } {
+            0 => {}
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+            // Ready, or an error the read will report
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// A console cannot be polled this way, so there the read waits for a line
+/// even after the connection has closed
+#[cfg(not(unix))]
+pub(crate) fn readable(done: &AtomicBool) -> bool {
+    !done.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parses_client_and_scan_operands() {
+        let parsed = options(&["-vw", "2.5", "example.com", "80"]).unwrap();
+        assert_eq!(parsed.host.as_deref(), Some("example.com"));
+        assert_eq!(parsed.ports, vec![80]);
+        assert!(parsed.verbose);
+        assert_eq!(parsed.wait, Some(Duration::from_millis(2500)));
+
+        let parsed = options(&["-z", "host", "20-22", "80"]).unwrap();
+        assert_eq!(parsed.ports, vec![20, 21, 22, 80]);
+
+        assert_eq!(
+            options(&["host", "20-22"]).unwrap_err(),
+            "port range '20-22' needs -z"
+        );
+        assert_eq!(
+            options(&["host", "80", "81"]).unwrap_err(),
+            "extra operand '81'"
+        );
+        assert_eq!(
+            options(&["-z", "host", "22-20"]).unwrap_err(),
+            "invalid port range '22-20'"
+        );
+        assert_eq!(options(&["host", "0"]).unwrap_err(), "invalid port '0'");
+        assert_eq!(options(&["host"]).unwrap_err(), "missing port number");
+        assert_eq!(options(&[]).unwrap_err(), "missing host operand");
+        assert_eq!(
+            options(&["-p", "80", "host", "80"]).unwrap_err(),
+            "option -p requires -l"
+        );
+    }
+
+    #[test]
+    fn parses_the_forms_of_listen_mode() {
+        let parsed = options(&["-l", "-p", "8080"]).unwrap();
+        assert_eq!((parsed.host, parsed.ports), (None, vec![8080]));
+        let parsed = options(&["-lu", "8080"]).unwrap();
+        assert!(parsed.udp);
+        assert_eq!((parsed.host, parsed.ports), (None, vec![8080]));
+        let parsed = options(&["-l", "127.0.0.1", "8080"]).unwrap();
+        assert_eq!(parsed.host.as_deref(), Some("127.0.0.1"));
+        let parsed = options(&["-lp8080", "::1"]).unwrap();
+        assert_eq!(parsed.host.as_deref(), Some("::1"));
+        assert_eq!(parsed.ports, vec![8080]);
+
+        assert_eq!(options(&["-l"]).unwrap_err(), "missing port number");
+        assert_eq!(options(&["-lz", "80"]).unwrap_err(), "cannot use -z and -l");
+        assert_eq!(
+            options(&["-l", "-p", "1", "a", "b"]).unwrap_err(),
+            "extra operand 'b'"
+        );
+        assert_eq!(
+            options(&["-w"]).unwrap_err(),
+            "option requires an argument -- 'w'"
+        );
+        assert_eq!(options(&["-x"]).unwrap_err(), "invalid option -- 'x'");
+        assert_eq!(
+            options(&["-w", "0", "h", "1"]).unwrap_err(),
+            "invalid timeout '0'"
+        );
+    }
+
+    #[test]
+    fn words_hal_failures_like_nc() {
+        let err = network::connect_tcp(
+            &ConnectionTarget::Host {
+                host: "127.0.0.1".to_string(),
+                port: 9,
+            },
+            Some(Duration::from_millis(1)),
+        );
+        if let Err(e) = err {
+            let message = failure(&e, "tcp");
+            assert!(
+                message.starts_with("connect to 127.0.0.1 port 9 (tcp) failed: "),
+                "{message}"
+            );
+        }
+        assert_eq!(
+            target("example.com", 80, true).unwrap_err(),
+            "example.com: Name or service not known"
+        );
+    }
+}
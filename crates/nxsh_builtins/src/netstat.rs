@@ -1,349 +1,335 @@
-//! `netstat` builtin - Network socket status utility with cross-platform support.
+//! `netstat` builtin - list open sockets and the processes holding them
 //!
-//! Delegates to the platform's `netstat` (or equivalent) command when available.
-//! On modern Linux distributions where `ss` replaces `netstat`, attempts to call
-//! `ss` with compatible arguments. When no external tools are available, provides
-//! a basic internal implementation for common socket listing functionality.
+//! Syntax:
+//!   netstat [-atulpn46] [--all] [--listening] [--tcp] [--udp] [--program]
+//!
+//! Without options the connected TCP and UDP sockets are listed; `-l` lists
+//! the listening ones instead, which for UDP are those without a peer, and
+//! `-a` lists both. `-t` and `-u` keep only TCP or only UDP sockets, and
+//! `-4` and `-6` only those on IPv4 or IPv6. `-p` adds the ID and name of
+//! each socket's owner, which show only for processes the shell may
+//! inspect. Addresses and ports are always printed as numbers; `-n` is
+//! accepted for compatibility.
+//!
+//! The sockets are read from `/proc/net` on Linux, from `lsof` on macOS and
+//! from the IP helper tables on Windows.
+//!
+//! In a structured pipeline `netstat` takes the same options and gives a
+//! table with the columns proto, local_address, local_port,
+//! remote_address, remote_port, state, pid and program:
+//!
+//!   netstat -l | where local_port -lt 1024 | select local_port program
+//!
+//! The exit status is 0 on success and 1 for an invalid option or when the
+//! sockets could not be read.
 
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::socket::list_sockets;
+use nxsh_hal::{SocketEntry, SocketProtocol};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::SocketAddr;
 
-#[derive(Debug, Clone)]
-#[derive(Default)]
-pub struct NetstatOptions {
-    all: bool,
-    listening: bool,
-    numeric: bool,
-    tcp: bool,
-    udp: bool,
-    process: bool,
-    verbose: bool,
-    continuous: bool,
-    use_internal: bool,
-}
+/// The `netstat` builtin command implementation
+pub struct NetstatCommand;
 
+impl Builtin for NetstatCommand {
+    fn name(&self) -> &'static str {
+        "netstat"
+    }
 
-/// Entry point for the `netstat` builtin.
-pub fn netstat_cli(args: &[String]) -> Result<()> {
-    let options = parse_netstat_args(args)?;
-    
-    // Prefer the full-featured system implementation when present (unless forced internal).
-    if !options.use_internal {
-        if let Ok(result) = try_external_netstat(args) {
-            return result;
-        }
-        
-        if options.verbose {
-            println!("netstat: external binary not found, using internal implementation");
-        }
+    fn synopsis(&self) -> &'static str {
+        "List open sockets and their processes"
     }
-    
-    // Use internal implementation
-    run_internal_netstat(&options)
-}
 
-fn try_external_netstat(args: &[String]) -> Result<Result<()>> {
-    // Preferred backends in order.
-    let backends = if cfg!(windows) {
-        vec!["netstat.exe", "netstat"]
-    } else {
-        vec!["netstat", "ss"]
-    };
+    fn description(&self) -> &'static str {
+        "List connected or listening TCP and UDP sockets with their addresses, state \
+         and owning process."
+    }
 
-    for bin in backends {
-        if let Ok(path) = which(bin) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("netstat: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
-        }
+    fn usage(&self) -> &'static str {
+        "netstat [-atulpn46] [--all] [--listening] [--tcp] [--udp] [--program]"
     }
 
-    Err(anyhow!("netstat: no suitable backend found"))
-}
+    fn help(&self) -> &'static str {
+        "List open sockets. Use 'netstat -tlp' for the listening TCP ports and their \
+         programs, or 'netstat -l | where local_port -eq 22' in a structured pipeline."
+    }
 
-fn parse_netstat_args(args: &[String]) -> Result<NetstatOptions> {
-    let mut options = NetstatOptions::default();
-    
-    for arg in args {
-        match arg.as_str() {
-            "-h" | "--help" => {
-                print_netstat_help();
-                std::process::exit(0);
-            }
-            "-a" | "--all" => {
-                options.all = true;
-            }
-            "-l" | "--listening" => {
-                options.listening = true;
-            }
-            "-n" | "--numeric" => {
-                options.numeric = true;
-            }
-            "-t" | "--tcp" => {
-                options.tcp = true;
-            }
-            "-u" | "--udp" => {
-                options.udp = true;
-            }
-            "-p" | "--programs" => {
-                options.process = true;
-            }
-            "-v" | "--verbose" => {
-                options.verbose = true;
-            }
-            "-c" | "--continuous" => {
-                options.continuous = true;
-            }
-            "--internal" => {
-                options.use_internal = true;
-            }
-            arg if arg.starts_with('-') => {
-                // Handle combined flags like -an, -tulpn
-                for ch in arg.chars().skip(1) {
-                    match ch {
-                        'a' => options.all = true,
-                        'l' => options.listening = true,
-                        'n' => options.numeric = true,
-                        't' => options.tcp = true,
-                        'u' => options.udp = true,
-                        'p' => options.process = true,
-                        'v' => options.verbose = true,
-                        'c' => options.continuous = true,
-                        _ => return Err(anyhow!("netstat: unknown option: -{}", ch)),
-                    }
-                }
-            }
-            _ => {
-                return Err(anyhow!("netstat: unknown argument: {}", arg));
-            }
-        }
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
     }
-    
-    Ok(options)
 }
 
-fn print_netstat_help() {
-    println!("Usage: netstat [options]");
-    println!();
-    println!("Options:");
-    println!("  -h, --help           Show this help message");
-    println!("  -a, --all            Show all sockets (default: connected)");
-    println!("  -l, --listening      Show only listening ports");
-    println!("  -n, --numeric        Show numerical addresses instead of resolving hosts");
-    println!("  -t, --tcp            Show TCP sockets");
-    println!("  -u, --udp            Show UDP sockets");
-    println!("  -p, --programs       Show PID and process name");
-    println!("  -v, --verbose        Enable verbose output");
-    println!("  -c, --continuous     Continuous listing");
-    println!("  --internal           Force use of internal implementation");
-    println!();
-    println!("Examples:");
-    println!("  netstat -a           Show all connections");
-    println!("  netstat -an          Show all connections with numeric addresses");
-    println!("  netstat -tulpn       Show TCP/UDP listening ports with processes");
-    println!("  netstat -l           Show only listening ports");
-    println!();
-    println!("Note: Internal implementation provides basic socket information");
-    println!("      Install system netstat/ss for complete functionality");
+/// Run netstat for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
 }
 
-fn run_internal_netstat(options: &NetstatOptions) -> Result<()> {
-    if !options.tcp && !options.udp {
-        // Default to both if neither specified
-        return run_both_protocols(options);
-    }
-    
-    println!("Active Internet connections ({})", 
-             if options.listening { "only servers" } else { "w/o servers" });
-    println!("{:<5} {:<6} {:<6} {:<23} {:<23} {:<10}", 
-             "Proto", "Recv-Q", "Send-Q", "Local Address", "Foreign Address", "State");
-    
-    if options.tcp {
-        show_tcp_connections(options)?;
-    }
-    
-    if options.udp {
-        show_udp_connections(options)?;
-    }
-    
-    Ok(())
+/// The sockets `args` select, for the structured `netstat`
+pub(crate) fn selected_sockets(args: &[String]) -> Result<Vec<SocketEntry>, String> {
+    Options::parse(args)?.sockets()
 }
 
-fn run_both_protocols(options: &NetstatOptions) -> Result<()> {
-    let mut tcp_opts = options.clone();
-    tcp_opts.tcp = true;
-    tcp_opts.udp = false;
-    
-    let mut udp_opts = options.clone();
-    udp_opts.tcp = false;
-    udp_opts.udp = true;
-    
-    run_internal_netstat(&tcp_opts)?;
-    println!();
-    run_internal_netstat(&udp_opts)?;
-    
-    Ok(())
+/// The protocol column: `tcp` or `udp`, with a 6 for IPv6 sockets
+pub(crate) fn protocol_name(socket: &SocketEntry) -> String {
+    let suffix = if socket.local.is_ipv6() { "6" } else { "" };
+    format!("{}{suffix}", socket.protocol.name())
 }
 
-fn show_tcp_connections(options: &NetstatOptions) -> Result<()> {
-    // This is a simplified implementation
-    // In a real implementation, you would read from /proc/net/tcp on Linux
-    // or use system APIs on Windows
-    
-    if cfg!(windows) {
-        show_windows_connections("tcp", options)
-    } else {
-        show_unix_connections("tcp", options)
-    }
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
 }
 
-fn show_udp_connections(options: &NetstatOptions) -> Result<()> {
-    if cfg!(windows) {
-        show_windows_connections("udp", options)
-    } else {
-        show_unix_connections("udp", options)
+fn run(args: &[String]) -> Outcome {
+    let listing = Options::parse(args).and_then(|options| {
+        let sockets = options.sockets()?;
+        Ok(render(&sockets, options.program))
+    });
+    match listing {
+        Ok(stdout) => Outcome {
+            stdout,
+            stderr: String::new(),
+            status: 0,
+        },
+        Err(message) => Outcome {
+            stdout: String::new(),
+            stderr: format!("netstat: {message}\n"),
+            status: 1,
+        },
     }
 }
 
-#[cfg(windows)]
-fn show_windows_connections(protocol: &str, _options: &NetstatOptions) -> Result<()> {
-    // Use PowerShell to get network connections on Windows
-    let mut cmd = Command::new("powershell");
-    cmd.arg("-Command");
-    
-    let ps_command = if protocol == "tcp" {
-        "Get-NetTCPConnection | Select-Object LocalAddress,LocalPort,RemoteAddress,RemotePort,State | Format-Table -AutoSize"
-    } else {
-        "Get-NetUDPEndpoint | Select-Object LocalAddress,LocalPort | Format-Table -AutoSize"
-    };
-    
-    cmd.arg(ps_command);
-    
-    match cmd.output() {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines().skip(3) { // Skip headers
-                if !line.trim().is_empty() {
-                    println!("{line}");
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+struct Options {
+    /// Connected sockets, the default
+    connected: bool,
+    /// `-l`, or with `-a` as well
+    listening: bool,
+    tcp: bool,
+    udp: bool,
+    ipv4: bool,
+    ipv6: bool,
+    /// `-p`, adding the owning process
+    program: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            connected: true,
+            listening: false,
+            tcp: false,
+            udp: false,
+            ipv4: false,
+            ipv6: false,
+            program: false,
+        };
+        let mut all = false;
+        let mut flags = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--all" => flags.push('a'),
+                "--listening" => flags.push('l'),
+                "--tcp" => flags.push('t'),
+                "--udp" => flags.push('u'),
+                "--numeric" => flags.push('n'),
+                "--program" | "--programs" => flags.push('p'),
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"))
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    flags.extend(short[1..].chars())
                 }
+                operand => return Err(format!("unexpected argument '{operand}'")),
+            }
+        }
+        for flag in flags {
+            match flag {
+                'a' => all = true,
+                'l' => options.listening = true,
+                't' => options.tcp = true,
+                'u' => options.udp = true,
+                '4' => options.ipv4 = true,
+                '6' => options.ipv6 = true,
+                'p' => options.program = true,
+                'n' => {}
+                _ => return Err(format!("invalid option -- '{flag}'")),
             }
         }
-        Err(_) => {
-            // Fallback to basic message
-            println!("{:<5} {:<6} {:<6} {:<23} {:<23} {:<10}", 
-                     protocol.to_uppercase(), "0", "0", "0.0.0.0:*", "*:*", "UNKNOWN");
-            println!("(Use system netstat for detailed information)");
+        if all {
+            options.listening = true;
+        } else if options.listening {
+            options.connected = false;
         }
+        // Naming neither protocol or neither family means both
+        if !options.tcp && !options.udp {
+            options.tcp = true;
+            options.udp = true;
+        }
+        if !options.ipv4 && !options.ipv6 {
+            options.ipv4 = true;
+            options.ipv6 = true;
+        }
+        Ok(options)
     }
-    
-    Ok(())
-}
 
-#[cfg(not(windows))]
-fn show_windows_connections(_protocol: &str, _options: &NetstatOptions) -> Result<()> {
-    // This should never be called on non-Windows
-    Ok(())
-}
+    fn selects(&self, socket: &SocketEntry) -> bool {
+        let protocol = match socket.protocol {
+            SocketProtocol::Tcp => self.tcp,
+            SocketProtocol::Udp => self.udp,
+        };
+        let family = if socket.local.is_ipv6() {
+            self.ipv6
+        } else {
+            self.ipv4
+        };
+        let kind = if socket.is_listening() {
+            self.listening
+        } else {
+            self.connected
+        };
+        protocol && family && kind
+    }
 
-#[cfg(not(windows))]
-fn show_unix_connections(protocol: &str, options: &NetstatOptions) -> Result<()> {
-    // Try to read from /proc/net/tcp or /proc/net/udp
-    let proc_file = format!("/proc/net/{}", protocol);
-    
-    match std::fs::read_to_string(&proc_file) {
-        Ok(content) => {
-            for (i, line) in content.lines().enumerate() {
-                if i == 0 { continue; } // Skip header
-                
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 4 {
-                    let local = parse_socket_addr(parts[1]);
-                    let remote = parse_socket_addr(parts[2]);
-                    let state = if protocol == "tcp" {
-                        parse_tcp_state(parts[3])
-                    } else {
-                        ""
-                    };
-                    
-                    if options.listening && state != "LISTEN" && protocol == "tcp" {
-                        continue;
-                    }
-                    
-                    println!("{:<5} {:<6} {:<6} {:<23} {:<23} {:<10}", 
-                             protocol, "0", "0", local, remote, state);
-                }
-            }
-        }
-        Err(_) => {
-            // Fallback message
-            println!("{:<5} {:<6} {:<6} {:<23} {:<23} {:<10}", 
-                     protocol.to_uppercase(), "0", "0", "0.0.0.0:*", "*:*", "UNKNOWN");
-            println!("(Unable to read {}, use system netstat for detailed information)", proc_file);
-        }
+    fn sockets(&self) -> Result<Vec<SocketEntry>, String> {
+        let sockets = list_sockets().map_err(|e| e.to_string())?;
+        Ok(sockets
+            .into_iter()
+            .filter(|socket| self.selects(socket))
+            .collect())
     }
-    
-    Ok(())
 }
 
-#[cfg(windows)]
-fn show_unix_connections(_protocol: &str, _options: &NetstatOptions) -> Result<()> {
-    // This should never be called on Windows
-    Ok(())
+/// An address, or the unspecified one with `*` for the port of a socket
+/// without a peer
+fn address(socket: &SocketEntry, remote: Option<SocketAddr>) -> String {
+    match remote {
+        Some(remote) => remote.to_string(),
+        None if socket.local.is_ipv6() => "[::]:*".to_string(),
+        None => "0.0.0.0:*".to_string(),
+    }
 }
 
-fn parse_socket_addr(hex_addr: &str) -> String {
-    if hex_addr.len() < 9 { return hex_addr.to_string(); }
-    
-    let ip_part = &hex_addr[0..8];
-    let port_part = &hex_addr[9..13];
-    
-    // Parse IP (little-endian)
-    if let (Ok(ip_num), Ok(port_num)) = (u32::from_str_radix(ip_part, 16), u16::from_str_radix(port_part, 16)) {
-        let ip = std::net::Ipv4Addr::from(ip_num.to_be());
-        format!("{ip}:{port_num}")
-    } else {
-        hex_addr.to_string()
+/// Lay out the sockets in left aligned columns, the last one unpadded
+fn render(sockets: &[SocketEntry], program: bool) -> String {
+    let mut header = vec!["Proto", "Local Address", "Foreign Address", "State"];
+    if program {
+        header.push("PID/Program name");
     }
-}
+    let rows: Vec<Vec<String>> = sockets
+        .iter()
+        .map(|socket| {
+            let mut row = vec![
+                protocol_name(socket),
+                socket.local.to_string(),
+                address(socket, socket.remote),
+                socket.state.name().to_string(),
+            ];
+            if program {
+                row.push(match (socket.pid, &socket.program) {
+                    (Some(pid), Some(name)) => format!("{pid}/{name}"),
+                    (Some(pid), None) => pid.to_string(),
+                    (None, _) => "-".to_string(),
+                });
+            }
+            row
+        })
+        .collect();
 
-fn parse_tcp_state(hex_state: &str) -> &'static str {
-    match hex_state {
-        "01" => "ESTABLISHED",
-        "02" => "SYN_SENT",
-        "03" => "SYN_RECV",
-        "04" => "FIN_WAIT1",
-        "05" => "FIN_WAIT2",
-        "06" => "TIME_WAIT",
-        "07" => "CLOSE",
-        "08" => "CLOSE_WAIT",
-        "09" => "LAST_ACK",
-        "0A" => "LISTEN",
-        "0B" => "CLOSING",
-        _ => "UNKNOWN",
+    let mut widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
     }
+    let mut out = String::new();
+    let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+    for cells in std::iter::once(&header).chain(&rows) {
+        for (index, cell) in cells.iter().enumerate() {
+            if index + 1 == cells.len() {
+                out.push_str(cell);
+            } else {
+                let _ = write!(out, "{cell:<width$} ", width = widths[index]);
+            }
+        }
+        out.push('\n');
+    }
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use nxsh_hal::SocketState;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn options_pick_kinds_protocols_and_families() {
+        let parsed = options(&["-tlp"]).unwrap();
+        assert!(parsed.listening && !parsed.connected);
+        assert!(parsed.tcp && !parsed.udp);
+        assert!(parsed.ipv4 && parsed.ipv6 && parsed.program);
+
+        let parsed = options(&["-a", "-6", "--udp"]).unwrap();
+        assert!(parsed.listening && parsed.connected);
+        assert!(!parsed.tcp && parsed.udp);
+        assert!(!parsed.ipv4 && parsed.ipv6);
+
+        assert_eq!(options(&["-x"]).unwrap_err(), "invalid option -- 'x'");
+        assert_eq!(
+            options(&["eth0"]).unwrap_err(),
+            "unexpected argument 'eth0'"
+        );
+    }
+
     #[test]
-    fn test_parse_netstat_args() {
-        let args = vec!["-an".to_string()];
-        let options = parse_netstat_args(&args).expect("Failed to parse valid netstat args");
-        assert!(options.all);
-        assert!(options.numeric);
-        
-        let args = vec!["-tulpn".to_string()];
-        let options = parse_netstat_args(&args).expect("Failed to parse netstat args with multiple flags");
-        assert!(options.tcp);
-        assert!(options.udp);
-        assert!(options.listening);
-        assert!(options.process);
-        assert!(options.numeric);
+    fn renders_addresses_states_and_owners() {
+        let sockets = [
+            SocketEntry {
+                protocol: SocketProtocol::Tcp,
+                local: "0.0.0.0:22".parse().unwrap(),
+                remote: None,
+                state: SocketState::Listen,
+                pid: Some(812),
+                program: Some("sshd".to_string()),
+            },
+            SocketEntry {
+                protocol: SocketProtocol::Udp,
+                local: "[::1]:53".parse().unwrap(),
+                remote: None,
+                state: SocketState::Unconnected,
+                pid: None,
+                program: None,
+            },
+        ];
+        assert_eq!(
+            render(&sockets, true),
+            "Proto Local Address Foreign Address State  PID/Program name\n\
+             tcp   0.0.0.0:22    0.0.0.0:*       LISTEN 812/sshd\n\
+             udp6  [::1]:53      [::]:*          UNCONN -\n"
+        );
+        assert_eq!(
+            render(&sockets[..1], false),
+            "Proto Local Address Foreign Address State\n\
+             tcp   0.0.0.0:22    0.0.0.0:*       LISTEN\n"
+        );
     }
 }
-
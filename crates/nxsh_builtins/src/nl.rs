@@ -1,147 +1,359 @@
-use anyhow::Result;
-use std::io::{self, BufRead, BufReader};
-use std::fs::File;
-
-/// CLI wrapper function for nl command (number lines)
-pub fn nl_cli(args: &[String]) -> Result<()> {
-    let mut number_format = "%6d\t".to_string();
-    let mut number_width: usize = 6;
-    let mut number_separator: String = "\t".to_string();
-    let mut body_numbering = "t"; // t=non-empty lines, a=all lines, n=no lines, pREGEX
-    let mut body_pattern: Option<String> = None;
-    let mut start_number = 1;
-    let mut increment = 1;
-    let mut files = Vec::new();
-    let mut i = 0;
-    
-    while i < args.len() {
-        match args[i].as_str() {
-            "-b" | "--body-numbering" => {
-                if i + 1 < args.len() {
-                    let val = &args[i + 1];
-                    if let Some(p) = val.strip_prefix('p') {
-                        body_numbering = "p";
-                        body_pattern = Some(p.to_string());
-                    } else {
-                        body_numbering = val;
-                    }
-                    i += 1;
+//! `nl` builtin - number lines of files
+//!
+//! Syntax:
+//!   nl [-p] [-b STYLE] [-h STYLE] [-f STYLE] [-v START] [-i INCR] [-w WIDTH]
+//!      [-s SEP] [-n FORMAT] [-l COUNT] [-d CC] [FILE...]
+//!
+//! The FILEs, standard input when there are none, are read as one text
+//! made of logical pages. A page has a header, a body and a footer, each
+//! begun by a line holding only `\:\:\:`, `\:\:` or `\:` (with CC in place
+//! of `\:`); numbering restarts at each page unless `-p` is given. Each
+//! section numbers lines by its STYLE: `a` all lines, `t` non-empty lines
+//! (the body default), `n` none (the header and footer default) or `pBRE`
+//! lines matching the basic regular expression BRE. Numbers start at START
+//! and step by INCR; they are WIDTH columns wide in FORMAT `ln` (left
+//! justified), `rn` (right justified) or `rz` (zero padded), and followed
+//! by SEP, a TAB by default. With `-b a`, only every COUNTth of a run of
+//! empty lines is numbered.
+//!
+//! The exit status is 0 on success and 1 on any error.
+
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crate::grep::translate;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use regex::bytes::Regex;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The `nl` builtin command implementation
+pub struct NlCommand;
+
+impl Builtin for NlCommand {
+    fn name(&self) -> &'static str {
+        "nl"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Number lines of files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write each FILE with line numbers added, numbering the header, body and \
+         footer of each logical page by their own style."
+    }
+
+    fn usage(&self) -> &'static str {
+        "nl [-p] [-b STYLE] [-h STYLE] [-f STYLE] [-v START] [-i INCR] [-w WIDTH] [-s SEP] \
+         [-n ln|rn|rz] [-l COUNT] [-d CC] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Number lines of files. Use 'nl -ba -nrz -w3 file' to number every line as 001, 002, ..."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run nl for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Which lines of a section are numbered
+#[derive(Debug)]
+enum Style {
+    All,
+    NonEmpty,
+    None,
+    Matching(Regex),
+}
+
+impl Style {
+    fn parse(style: &str) -> Result<Self, String> {
+        match style {
+            "a" => Ok(Style::All),
+            "t" => Ok(Style::NonEmpty),
+            "n" => Ok(Style::None),
+            _ => match style.strip_prefix('p') {
+                Some(pattern) => translate(pattern, false)
+                    .and_then(|pattern| Regex::new(&pattern).map_err(|e| e.to_string()))
+                    .map(Style::Matching),
+                None => Err(format!("invalid line numbering style: '{style}'")),
+            },
+        }
+    }
+}
+
+/// How numbers are laid out in their column
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Left,
+    Right,
+    Zeros,
+}
+
+#[derive(Debug)]
+struct Options {
+    /// The styles of the header, body and footer
+    styles: [Style; 3],
+    start: i64,
+    increment: i64,
+    width: usize,
+    separator: String,
+    format: Format,
+    blank_join: usize,
+    delimiter: String,
+    renumber: bool,
+    files: Vec<String>,
+}
+
+/// Long options and the short flags they stand for
+const LONG: &[(&str, char)] = &[
+    ("body-numbering", 'b'),
+    ("header-numbering", 'h'),
+    ("footer-numbering", 'f'),
+    ("starting-line-number", 'v'),
+    ("line-increment", 'i'),
+    ("number-width", 'w'),
+    ("number-separator", 's'),
+    ("number-format", 'n'),
+    ("join-blank-lines", 'l'),
+    ("section-delimiter", 'd'),
+    ("no-renumber", 'p'),
+];
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            styles: [Style::None, Style::NonEmpty, Style::None],
+            start: 1,
+            increment: 1,
+            width: 6,
+            separator: "\t".to_string(),
+            format: Format::Right,
+            blank_join: 1,
+            delimiter: "\\:".to_string(),
+            renumber: true,
+            files: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let (flag, attached) = match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
                 }
-            }
-            "-n" | "--number-format" => {
-                if i + 1 < args.len() {
-                    match args[i + 1].as_str() {
-                        "ln" => number_format = "%-6d\t".to_string(),
-                        "rn" => number_format = "%6d\t".to_string(),
-                        "rz" => number_format = "%06d\t".to_string(),
-                        _ => number_format = "%6d\t".to_string(),
-                    }
-                    i += 1;
+                long if long.starts_with("--") => {
+                    let (name, value) = match long[2..].split_once('=') {
+                        Some((name, value)) => (name, Some(value)),
+                        None => (&long[2..], None),
+                    };
+                    let Some(&(_, flag)) = LONG.iter().find(|(long, _)| *long == name) else {
+                        return Err(format!("unrecognized option '{long}'"));
+                    };
+                    (flag, value.unwrap_or_default())
                 }
-            }
-            "-w" | "--number-width" => {
-                if i + 1 < args.len() { number_width = args[i + 1].parse().unwrap_or(6); i += 1; }
-            }
-            "-s" | "--number-separator" => {
-                if i + 1 < args.len() { number_separator = args[i + 1].clone(); i += 1; }
-            }
-            "-v" | "--starting-line-number" => {
-                if i + 1 < args.len() {
-                    start_number = args[i + 1].parse().unwrap_or(1);
-                    i += 1;
+                short if short.len() > 1 && short.starts_with('-') => {
+                    let flag = short[1..].chars().next().unwrap_or('-');
+                    (flag, &short[1 + flag.len_utf8()..])
                 }
-            }
-            "-i" | "--line-increment" => {
-                if i + 1 < args.len() {
-                    increment = args[i + 1].parse().unwrap_or(1);
-                    i += 1;
+                _ => {
+                    options.files.push(arg.clone());
+                    continue;
                 }
+            };
+            if flag == 'p' {
+                options.renumber = false;
+                continue;
             }
-            "-h" | "--help" => {
-                println!("nl - number lines of files");
-                println!("Usage: nl [OPTION]... [FILE]...");
-                println!("  -b, --body-numbering=STYLE    use STYLE for numbering body lines");
-                println!("  -n, --number-format=FORMAT    use FORMAT for line numbers");
-                println!("  -v, --starting-line-number=N  first line number");
-                println!("  -i, --line-increment=N        line number increment");
-                println!("  -h, --help                    display this help and exit");
-                return Ok(());
-            }
-            arg if !arg.starts_with('-') => {
-                files.push(arg.to_string());
-            }
-            _ => {
-                eprintln!("nl: unrecognized option '{}'", args[i]);
-                return Err(anyhow::anyhow!("Invalid option"));
+            if !"bhfviwsnld".contains(flag) {
+                return Err(format!("invalid option -- '{flag}'"));
             }
+            let value = if attached.is_empty() {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?
+            } else {
+                attached.to_string()
+            };
+            options.set(flag, value)?;
+        }
+        if options.files.is_empty() {
+            options.files.push("-".to_string());
         }
-        i += 1;
+        Ok(options)
     }
-    
-    if files.is_empty() {
-        // Read from stdin
-        let stdin = io::stdin();
-        let lines: Vec<String> = stdin.lock().lines().collect::<Result<_, _>>()?;
-        number_lines(&lines, &number_format, number_width, &number_separator, body_numbering, body_pattern.as_deref(), start_number, increment)?;
-    } else {
-        // Read from files
-        for filename in files {
-            let file = File::open(&filename)?;
-            let reader = BufReader::new(file);
-            let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
-            number_lines(&lines, &number_format, number_width, &number_separator, body_numbering, body_pattern.as_deref(), start_number, increment)?;
+
+    fn set(&mut self, flag: char, value: String) -> Result<(), String> {
+        let number = |what: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| format!("invalid {what}: '{value}'"))
+        };
+        let positive = |what: &str| match value.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(format!("invalid {what}: '{value}'")),
+        };
+        match flag {
+            'b' => self.styles[1] = Style::parse(&value)?,
+            'h' => self.styles[0] = Style::parse(&value)?,
+            'f' => self.styles[2] = Style::parse(&value)?,
+            'v' => self.start = number("starting line number")?,
+            'i' => self.increment = number("line number increment")?,
+            'w' => self.width = positive("line number field width")?,
+            'l' => self.blank_join = positive("line number of blank lines")?,
+            'n' => {
+                self.format = match value.as_str() {
+                    "ln" => Format::Left,
+                    "rn" => Format::Right,
+                    "rz" => Format::Zeros,
+                    _ => return Err(format!("invalid line numbering format: '{value}'")),
+                }
+            }
+            'd' => {
+                self.delimiter = match value.chars().count() {
+                    1 => format!("{value}:"),
+                    _ => value,
+                }
+            }
+            _ => self.separator = value,
         }
+        Ok(())
     }
-    
-    Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-fn number_lines(
-    lines: &[String],
-    format: &str,
-    width: usize,
-    sep: &str,
-    numbering_style: &str,
-    pattern: Option<&str>,
-    start: i32,
-    increment: i32
-) -> Result<()> {
-    let mut line_number = start;
-    #[cfg(feature = "advanced-regex")]
-    let regex = if numbering_style == "p" { pattern.and_then(|p| fancy_regex::Regex::new(p).ok()) } else { None };
-    #[cfg(not(feature = "advanced-regex"))]
-    let regex: Option<()> = None;
-    
-    for line in lines {
-        let should_number = match numbering_style {
-            "a" => true,  // All lines
-            "t" => !line.trim().is_empty(),  // Non-empty lines only
-            "n" => false, // No lines
-            "p" => {
-                #[cfg(feature = "advanced-regex")]
-                { regex.as_ref().map(|r| r.is_match(line).unwrap_or(false)).unwrap_or(false) }
-                #[cfg(not(feature = "advanced-regex"))]
-                { false }
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("nl: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let delimiters: Vec<Vec<u8>> = (1..=3)
+        .rev()
+        .map(|count| options.delimiter.repeat(count).into_bytes())
+        .collect();
+
+    let mut inputs = Inputs::new(cwd, stdin);
+    let mut section = 1;
+    let mut number = options.start;
+    let mut blank_run = 0;
+    let out = &mut outcome.stdout;
+    for name in &options.files {
+        let data = match inputs.read(name) {
+            Ok(data) => data,
+            Err(e) => {
+                outcome
+                    .stderr
+                    .extend(format!("nl: {name}: {}\n", io_message(&e)).as_bytes());
+                outcome.status = 1;
+                continue;
             }
-            _ => !line.trim().is_empty(),  // Default: non-empty lines
         };
-        
-        if should_number {
-            if format.contains("%-") {
-                print!("{line_number:<width$}{sep}{line}");
-            } else if format.contains("%0") {
-                print!("{line_number:0width$}{sep}{line}");
+        for line in lines(&data) {
+            if let Some(next) = delimiters.iter().position(|delimiter| delimiter == line) {
+                // A header, or a body without one, begins a new page
+                if options.renumber && (next == 0 || (next == 1 && section != 0)) {
+                    number = options.start;
+                }
+                section = next;
+                blank_run = 0;
+                out.push(b'\n');
+                continue;
+            }
+            let numbered = match &options.styles[section] {
+                Style::All if line.is_empty() => {
+                    blank_run += 1;
+                    blank_run % options.blank_join == 0
+                }
+                Style::All => true,
+                Style::NonEmpty => !line.is_empty(),
+                Style::None => false,
+                Style::Matching(regex) => regex.is_match(line),
+            };
+            if !line.is_empty() {
+                blank_run = 0;
+            }
+            if numbered {
+                let width = options.width;
+                let field = match options.format {
+                    Format::Left => format!("{number:<width$}"),
+                    Format::Right => format!("{number:>width$}"),
+                    Format::Zeros => format!("{number:0width$}"),
+                };
+                out.extend_from_slice(field.as_bytes());
+                out.extend_from_slice(options.separator.as_bytes());
+                number += options.increment;
             } else {
-                print!("{line_number:>width$}{sep}{line}");
+                let blank = options.width + options.separator.len();
+                out.resize(out.len() + blank, b' ');
             }
-            line_number += increment;
-        } else {
-            print!("{blank}{sep}{line}", blank = " ".repeat(width));
+            out.extend_from_slice(line);
+            out.push(b'\n');
         }
-        println!();
     }
-    
-    Ok(())
+    outcome
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nl(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, Path::new("."), &mut input.as_bytes());
+        String::from_utf8(outcome.stdout).unwrap()
+    }
+
+    #[test]
+    fn numbers_by_style_and_format() {
+        assert_eq!(nl(&[], "a\n\nb\n"), "     1\ta\n       \n     2\tb\n");
+        assert_eq!(
+            nl(&["-ba", "-nln", "-w2", "-s: "], "a\n\n"),
+            "1 : a\n2 : \n"
+        );
+        assert_eq!(
+            nl(&["-nrz", "-w3", "-v", "9", "-i", "2"], "a\nb\n"),
+            "009\ta\n011\tb\n"
+        );
+        assert_eq!(nl(&["-bp^x", "-w1"], "xa\nb\n"), "1\txa\n  b\n");
+        assert_eq!(nl(&["-ba", "-l2", "-w1"], "\n\n\n"), "  \n1\t\n  \n");
+    }
+
+    #[test]
+    fn restarts_at_each_page() {
+        let input = "\\:\\:\\:\nhead\n\\:\\:\nbody\n\\:\nfoot\n\\:\\:\\:\n\\:\\:\nagain\n";
+        assert_eq!(
+            nl(&["-w1", "-hn"], input),
+            "\n  head\n\n1\tbody\n\n  foot\n\n\n1\tagain\n"
+        );
+        assert_eq!(nl(&["-w1", "-p"], input).lines().last(), Some("2\tagain"));
+    }
+}
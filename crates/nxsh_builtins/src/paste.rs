@@ -1,134 +1,282 @@
-//! `paste` command  Ehorizontal file merging.
+//! `paste` builtin - merge lines of files
 //!
-//! Supported subset:
-//!   paste [-d DELIM] FILE...
-//!   • FILE of "-" means STDIN
-//!   • Lines from each file are joined with DELIM (default TAB) pair-wise.
-//!   • If files have different lengths, missing fields are treated as empty.
-//!   • Serial (-s) mode is supported: files are concatenated one by one, joining lines with DELIM.
+//! Syntax:
+//!   paste [-s] [-d LIST] [FILE...]
 //!
-//! This implementation aims for practical daily use without covering every GNU paste option.
+//! Line N of the output joins line N of every FILE, separated by TABs; a
+//! FILE that has run out contributes an empty field. With `-s` each FILE
+//! is instead pasted onto a single line of its own. `-d` replaces the TAB
+//! with the characters of LIST in turn, where `\n`, `\t`, `\\` and `\0`
+//! (no delimiter) are understood. A FILE of `-` is standard input; naming
+//! it several times deals its lines out to each in turn.
+//!
+//! The exit status is 0 on success and 1 when a FILE cannot be read.
 
-use anyhow::{anyhow, Result};
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Read, Write};
 use std::path::Path;
 
-pub fn paste_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("paste: missing file operands"));
+/// The `paste` builtin command implementation
+pub struct PasteCommand;
+
+impl Builtin for PasteCommand {
+    fn name(&self) -> &'static str {
+        "paste"
     }
-    let mut idx = 0;
-    let mut delim = '\t';
-    let mut serial = false;
-    while idx < args.len() {
-        match args[idx].as_str() {
-            "-d" => {
-                idx += 1;
-                if idx >= args.len() { return Err(anyhow!("paste: option requires argument -- d")); }
-                let d = unescape(&args[idx])?;
-                if d.chars().count() != 1 { return Err(anyhow!("paste: delimiter must be single character")); }
-                delim = d.chars().next().unwrap();
-                idx += 1;
-            }
-            "-s" => { serial = true; idx += 1; }
-            "--" => { idx += 1; break; }
-            _ => break,
-        }
+
+    fn synopsis(&self) -> &'static str {
+        "Merge lines of files"
     }
 
-    if idx >= args.len() {
-        return Err(anyhow!("paste: missing file operands"));
+    fn description(&self) -> &'static str {
+        "Write lines made of the corresponding lines of each FILE separated by TABs, \
+         or with -s each FILE on one line."
     }
 
-    if serial {
-        process_paste_serial(&args[idx..], delim)?;
-    } else {
-        // Open files/stdin
-        let mut readers: Vec<Box<dyn BufRead>> = Vec::new();
-        for p in &args[idx..] {
-            if p == "-" {
-                readers.push(Box::new(BufReader::new(io::stdin())));
-            } else {
-                let f = File::open(Path::new(p))?;
-                readers.push(Box::new(BufReader::new(f)));
-            }
-        }
-        process_paste(&mut readers, delim)?;
+    fn usage(&self) -> &'static str {
+        "paste [-s] [-d LIST] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Merge lines of files. Use 'paste -sd, list' to join a list with commas."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
-    Ok(())
 }
 
-fn process_paste(readers: &mut [Box<dyn BufRead>], delim: char) -> Result<()> {
-    let mut buffers: Vec<String> = vec![String::new(); readers.len()];
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
+/// Run paste for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
 
-    loop {
-        let mut eof_count = 0;
-        for (i, rdr) in readers.iter_mut().enumerate() {
-            buffers[i].clear();
-            let n = rdr.read_line(&mut buffers[i])?;
-            if n == 0 {
-                eof_count += 1;
-            } else {
-                // trim trailing newline
-                if buffers[i].ends_with('\n') { buffers[i].pop(); }
-                if buffers[i].ends_with('\r') { buffers[i].pop(); }
-            }
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    serial: bool,
+    /// Each delimiter of LIST, empty for `\0`
+    delimiters: Vec<Vec<u8>>,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            serial: false,
+            delimiters: vec![b"\t".to_vec()],
+            files: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let list = match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "-s" | "--serial" => {
+                    options.serial = true;
+                    continue;
+                }
+                "-d" | "--delimiters" => iter
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| "option requires an argument -- 'd'".to_string())?,
+                long if long.starts_with("--delimiters=") => long["--delimiters=".len()..].into(),
+                short if short.starts_with("-sd") => {
+                    options.serial = true;
+                    match &short[3..] {
+                        "" => iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| "option requires an argument -- 'd'".to_string())?,
+                        list => list.to_string(),
+                    }
+                }
+                short if short.starts_with("-d") && short.len() > 2 => short[2..].to_string(),
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    let flag = short.chars().nth(1).unwrap_or('-');
+                    return Err(format!("invalid option -- '{flag}'"));
+                }
+                _ => {
+                    options.files.push(arg.clone());
+                    continue;
+                }
+            };
+            options.delimiters = delimiters(&list)?;
         }
-        if eof_count == readers.len() { break; }
-        for (i, buf) in buffers.iter().enumerate() {
-            if i != 0 { write!(handle, "{delim}")?; }
-            handle.write_all(buf.as_bytes())?;
+        if options.files.is_empty() {
+            options.files.push("-".to_string());
         }
-        handle.write_all(b"\n")?;
+        Ok(options)
     }
-    Ok(())
 }
 
-fn process_paste_serial(paths: &[String], delim: char) -> Result<()> {
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
-    for (fi, p) in paths.iter().enumerate() {
-        let mut rdr: Box<dyn BufRead> = if p == "-" { Box::new(BufReader::new(io::stdin())) } else { Box::new(BufReader::new(File::open(Path::new(p))?)) };
-        let mut buf = String::new();
-        let mut first_line = true;
-        loop {
-            buf.clear();
-            let n = rdr.read_line(&mut buf)?;
-            if n == 0 { break; }
-            if buf.ends_with('\n') { buf.pop(); }
-            if buf.ends_with('\r') { buf.pop(); }
-            if !first_line { write!(handle, "{delim}")?; }
-            handle.write_all(buf.as_bytes())?;
-            first_line = false;
-        }
-        if fi + 1 < paths.len() { handle.write_all(b"\n")?; }
+/// The delimiters of LIST, one per character after escapes
+fn delimiters(list: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut delimiters = Vec::new();
+    let mut chars = list.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => {
+                    delimiters.push(Vec::new());
+                    continue;
+                }
+                Some(other) => other,
+                None => {
+                    return Err(format!(
+                        "delimiter list ends with an unescaped backslash: {list}"
+                    ))
+                }
+            },
+            c => c,
+        };
+        delimiters.push(c.to_string().into_bytes());
+    }
+    if delimiters.is_empty() {
+        delimiters.push(Vec::new());
     }
-    Ok(())
+    Ok(delimiters)
 }
 
-fn unescape(s: &str) -> Result<String> {
-    if let Some(rest) = s.strip_prefix('\\') {
-        Ok(match rest {
-            "t" => "\t".to_string(),
-            "n" => "\n".to_string(),
-            "r" => "\r".to_string(),
-            other => other.to_string(),
-        })
-    } else {
-        Ok(s.to_string())
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("paste: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+
+    // Standard input is read once and shared by every `-` operand
+    let mut inputs = Inputs::new(cwd, stdin);
+    let wants_stdin = options.files.iter().any(|name| name == "-");
+    let mut contents = Vec::new();
+    for name in std::iter::once("-").chain(options.files.iter().map(String::as_str)) {
+        if name == "-" && (!contents.is_empty() || !wants_stdin) {
+            contents.push(Vec::new());
+            continue;
+        }
+        match inputs.read(name) {
+            Ok(data) => contents.push(data),
+            Err(e) => {
+                outcome.stderr = format!("paste: {name}: {}\n", io_message(&e)).into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+        }
+    }
+    let stdin_data = contents.remove(0);
+    let stdin_lines = lines(&stdin_data);
+    let mut stdin_next = 0;
+    let file_lines: Vec<Vec<&[u8]>> = contents.iter().map(|data| lines(data)).collect();
+    let delimiter = |index: usize| options.delimiters[index % options.delimiters.len()].as_slice();
+
+    let out = &mut outcome.stdout;
+    if options.serial {
+        for (name, own) in options.files.iter().zip(&file_lines) {
+            let file = if name == "-" {
+                let rest = &stdin_lines[stdin_next..];
+                stdin_next = stdin_lines.len();
+                rest
+            } else {
+                own.as_slice()
+            };
+            for (index, line) in file.iter().enumerate() {
+                if index > 0 {
+                    out.extend_from_slice(delimiter(index - 1));
+                }
+                out.extend_from_slice(line);
+            }
+            out.push(b'\n');
+        }
+        return outcome;
+    }
+
+    let mut row = 0;
+    loop {
+        let mut fields = Vec::with_capacity(options.files.len());
+        let mut any = false;
+        for (name, own) in options.files.iter().zip(&file_lines) {
+            let line = if name == "-" {
+                stdin_next += 1;
+                stdin_lines.get(stdin_next - 1)
+            } else {
+                own.get(row)
+            };
+            any |= line.is_some();
+            fields.push(line.copied().unwrap_or_default());
+        }
+        if !any {
+            break;
+        }
+        for (index, field) in fields.into_iter().enumerate() {
+            if index > 0 {
+                out.extend_from_slice(delimiter(index - 1));
+            }
+            out.extend_from_slice(field);
+        }
+        out.push(b'\n');
+        row += 1;
     }
+    outcome
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn unesc() {
-        assert_eq!(unescape("\\t").unwrap(), "\t");
+    fn paste(args: &[&str], dir: &Path, input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut input.as_bytes());
+        String::from_utf8(outcome.stdout).unwrap()
     }
-} 
 
+    #[test]
+    fn merges_files_and_standard_input() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "1\n2\n3\n").unwrap();
+        std::fs::write(dir.path().join("b"), "x\ny\n").unwrap();
+        assert_eq!(paste(&["a", "b"], dir.path(), ""), "1\tx\n2\ty\n3\t\n");
+        assert_eq!(
+            paste(&["-d", ",:", "a", "b", "a"], dir.path(), ""),
+            "1,x:1\n2,y:2\n3,:3\n"
+        );
+        assert_eq!(paste(&["-", "-"], dir.path(), "a\nb\nc\n"), "a\tb\nc\t\n");
+        assert_eq!(paste(&["-sd,", "a", "b"], dir.path(), ""), "1,2,3\nx,y\n");
+        assert_eq!(
+            paste(&["-s", "-d", "\\0", "-"], dir.path(), "a\nb\n"),
+            "ab\n"
+        );
+    }
+}
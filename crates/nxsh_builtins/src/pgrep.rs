@@ -1,28 +1,465 @@
-//! `pgrep` builtin  Esearch processes by name (regex).
+//! `pgrep` and `pkill` builtins - find or signal processes by name
 //!
-//! Usage: `pgrep PATTERN` (POSIX ERE pattern). Prints matching PIDs, one per line.
-//! Options not yet supported (future: -l, -f, -x, etc.).
+//! Syntax:
+//!   pgrep [-acfilnovx] [-d DELIM] [-u USER,...] [-P PPID,...] [PATTERN]
+//!   pkill [-SIGNAL] [--signal SIGNAL] [-cefinovx] [-u USER,...] [-P PPID,...] [PATTERN]
+//!
+//! PATTERN is a regular expression matched against each process's name, or
+//! with `-f` against its whole command line. `-u` and `-P` keep only the
+//! processes of the given users or parents, and may stand in for PATTERN.
+//! The shell itself is never matched.
+//!
+//! `pgrep` prints the IDs of the matching processes, one per line or
+//! separated by DELIM; `-l` adds their names and `-a` their command lines.
+//! `pkill` sends them SIGNAL, given as a name like `HUP` or `SIGHUP` or a
+//! number, or SIGTERM when there is none; `-e` reports each process
+//! signalled.
+//!
+//! Options:
+//!   -c          print the number of matches instead
+//!   -f          match the whole command line instead of the name
+//!   -i          match without regard to case
+//!   -n          only the newest matching process
+//!   -o          only the oldest matching process
+//!   -v          match the processes PATTERN does not
+//!   -x          match the whole name or command line only
+//!   -u USER     only the processes of USER, or of any in a comma list
+//!   -P PPID     only the children of PPID, or of any in a comma list
+//!
+//! The exit status is 0 when a process matched, 1 when none did, 2 for a
+//! usage error and 3 when reading the processes or every signal failed.
+
+use crate::common::process_utils::execute_kill_target;
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::{ProcessEntry, ProcessSnapshot};
+use regex::{Regex, RegexBuilder};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// The `pgrep` builtin command implementation
+pub struct PgrepCommand;
+
+impl Builtin for PgrepCommand {
+    fn name(&self) -> &'static str {
+        "pgrep"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Find processes by name"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the IDs of the processes whose name or command line matches a regular \
+         expression, optionally only those of some users or parents."
+    }
+
+    fn usage(&self) -> &'static str {
+        "pgrep [-acfilnovx] [-d DELIM] [-u USER,...] [-P PPID,...] [PATTERN]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Find processes by name. Use 'pgrep -l ssh' to see names too or 'pgrep -f \
+         \"python app.py\"' to match whole command lines."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        Ok(run(Mode::Pgrep, args).into_result())
+    }
+}
+
+/// The `pkill` builtin command implementation
+pub struct PkillCommand;
+
+impl Builtin for PkillCommand {
+    fn name(&self) -> &'static str {
+        "pkill"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Signal processes by name"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send a signal, SIGTERM unless another is named, to the processes whose name \
+         or command line matches a regular expression."
+    }
+
+    fn usage(&self) -> &'static str {
+        "pkill [-SIGNAL] [--signal SIGNAL] [-cefinovx] [-u USER,...] [-P PPID,...] [PATTERN]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Signal processes by name. Use 'pkill -HUP nginx' to reload a server or \
+         'pkill -9 -f \"node server.js\"' to stop one command line."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        Ok(run(Mode::Pkill, args).into_result())
+    }
+}
+
+/// Run pgrep for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run(Mode::Pgrep, args).write()
+}
+
+/// Run pkill for the legacy dispatcher
+pub fn execute_pkill(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run(Mode::Pkill, args).write()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Pgrep,
+    Pkill,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Pgrep => "pgrep",
+            Mode::Pkill => "pkill",
+        }
+    }
+}
 
-use anyhow::{anyhow, Result};
-use regex::Regex;
-#[cfg(feature = "system-info")]
-use sysinfo::{ProcessExt, System, SystemExt};
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
 
-pub fn pgrep_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("pgrep: missing PATTERN"));
+impl Outcome {
+    fn into_result(self) -> ExecutionResult {
+        ExecutionResult::success(self.status)
+            .with_output(self.stdout.into_bytes())
+            .with_error(self.stderr.into_bytes())
+    }
+
+    fn write(self) -> BuiltinResult<i32> {
+        io::stdout().write_all(self.stdout.as_bytes())?;
+        io::stderr().write_all(self.stderr.as_bytes())?;
+        Ok(self.status)
+    }
+}
+
+/// Which listing pgrep prints
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Listing {
+    Pids,
+    Names,
+    CommandLines,
+}
+
+/// Parsed command line
+#[derive(Debug)]
+struct Options {
+    full: bool,
+    ignore_case: bool,
+    exact: bool,
+    invert: bool,
+    newest: bool,
+    oldest: bool,
+    count: bool,
+    echo: bool,
+    listing: Listing,
+    delimiter: String,
+    users: Vec<String>,
+    parents: Vec<u32>,
+    signal: i32,
+    pattern: Option<String>,
+}
+
+impl Options {
+    fn parse(mode: Mode, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            full: false,
+            ignore_case: false,
+            exact: false,
+            invert: false,
+            newest: false,
+            oldest: false,
+            count: false,
+            echo: false,
+            listing: Listing::Pids,
+            delimiter: "\n".to_string(),
+            users: Vec::new(),
+            parents: Vec::new(),
+            signal: 15,
+            pattern: None,
+        };
+        let mut iter = args.iter().enumerate();
+        while let Some((index, arg)) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    if let Some((_, pattern)) = iter.next() {
+                        options.set_pattern(pattern)?;
+                    }
+                    if let Some((_, extra)) = iter.next() {
+                        return Err(format!("only one pattern can be provided, not '{extra}'"));
+                    }
+                    break;
+                }
+                "--signal" if mode == Mode::Pkill => {
+                    let (_, spec) = iter
+                        .next()
+                        .ok_or("option '--signal' requires an argument")?;
+                    options.signal = signal(spec)?;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                // Like kill, pkill takes the signal as its first option
+                short
+                    if mode == Mode::Pkill
+                        && index == 0
+                        && short.len() > 1
+                        && short.starts_with('-')
+                        && nxsh_hal::signal::parse_signal(&short[1..]).is_some() =>
+                {
+                    options.signal = signal(&short[1..])?;
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match (flag, mode) {
+                            ('f', _) => options.full = true,
+                            ('i', _) => options.ignore_case = true,
+                            ('x', _) => options.exact = true,
+                            ('v', _) => options.invert = true,
+                            ('n', _) => options.newest = true,
+                            ('o', _) => options.oldest = true,
+                            ('c', _) => options.count = true,
+                            ('e', Mode::Pkill) => options.echo = true,
+                            ('l', Mode::Pgrep) => options.listing = Listing::Names,
+                            ('a', Mode::Pgrep) => options.listing = Listing::CommandLines,
+                            ('d', Mode::Pgrep) | ('u', _) | ('P', _) => {
+                                let value = match &short[2 + at..] {
+                                    "" => iter.next().map(|(_, value)| value.clone()).ok_or_else(
+                                        || format!("option requires an argument -- '{flag}'"),
+                                    )?,
+                                    attached => attached.to_string(),
+                                };
+                                options.apply(flag, &value)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                pattern => options.set_pattern(pattern)?,
+            }
+        }
+        if options.newest && options.oldest {
+            return Err("-n and -o cannot be given together".to_string());
+        }
+        if options.pattern.is_none() && options.users.is_empty() && options.parents.is_empty() {
+            return Err("no matching criteria specified".to_string());
+        }
+        Ok(options)
     }
-    let pattern = &args[0];
-    let re = Regex::new(pattern).map_err(|e| anyhow!("pgrep: invalid regex: {e}"))?;
 
-    let mut sys = System::new_all();
-    sys.refresh_processes();
+    fn set_pattern(&mut self, pattern: &str) -> Result<(), String> {
+        if self.pattern.is_some() {
+            return Err(format!("only one pattern can be provided, not '{pattern}'"));
+        }
+        self.pattern = Some(pattern.to_string());
+        Ok(())
+    }
 
-    for (pid, proc_) in sys.processes() {
-        if re.is_match(proc_.name()) {
-            println!("{}", pid);
+    fn apply(&mut self, flag: char, value: &str) -> Result<(), String> {
+        let items = value.split(',').filter(|item| !item.is_empty());
+        match flag {
+            'd' => self.delimiter = value.to_string(),
+            'u' => self.users.extend(items.map(str::to_string)),
+            _ => {
+                for item in items {
+                    let pid = item
+                        .parse()
+                        .map_err(|_| format!("invalid parent process ID '{item}'"))?;
+                    self.parents.push(pid);
+                }
+            }
         }
+        Ok(())
     }
-    Ok(())
-} 
 
+    fn regex(&self) -> Result<Option<Regex>, String> {
+        let Some(pattern) = &self.pattern else {
+            return Ok(None);
+        };
+        let pattern = if self.exact {
+            format!("^(?:{pattern})$")
+        } else {
+            pattern.clone()
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map(Some)
+            .map_err(|e| format!("invalid pattern: {e}"))
+    }
+}
+
+/// A signal number from a name or number given on the command line
+fn signal(spec: &str) -> Result<i32, String> {
+    nxsh_hal::signal::parse_signal(spec).ok_or_else(|| format!("unknown signal name '{spec}'"))
+}
+
+/// Whether `process` is selected by the pattern, users and parents
+fn matches(options: &Options, regex: Option<&Regex>, process: &ProcessEntry) -> bool {
+    let text = if options.full {
+        &process.command_line
+    } else {
+        &process.name
+    };
+    let named = match regex {
+        Some(regex) => regex.is_match(text),
+        None => true,
+    };
+    let selected = named
+        && (options.users.is_empty() || options.users.contains(&process.user))
+        && (options.parents.is_empty() || options.parents.contains(&process.ppid));
+    selected != options.invert
+}
+
+fn run(mode: Mode, args: &[String]) -> Outcome {
+    let name = mode.name();
+    let fail = |message: String, status: i32| Outcome {
+        stdout: String::new(),
+        stderr: format!("{name}: {message}\n"),
+        status,
+    };
+    let options = match Options::parse(mode, args) {
+        Ok(options) => options,
+        Err(message) => return fail(message, 2),
+    };
+    let regex = match options.regex() {
+        Ok(regex) => regex,
+        Err(message) => return fail(message, 2),
+    };
+    let snapshot = match ProcessSnapshot::capture() {
+        Ok(snapshot) => snapshot,
+        Err(e) => return fail(e.to_string(), 3),
+    };
+
+    let me = std::process::id();
+    let mut found: Vec<&ProcessEntry> = snapshot
+        .processes()
+        .iter()
+        .filter(|process| process.pid != me && matches(&options, regex.as_ref(), process))
+        .collect();
+    if options.newest || options.oldest {
+        // The latest start wins for -n and the earliest for -o, with the
+        // PID breaking ties
+        let key = |process: &&ProcessEntry| (process.start_time, process.pid);
+        let chosen = if options.newest {
+            found.iter().copied().max_by_key(key)
+        } else {
+            found.iter().copied().min_by_key(key)
+        };
+        found = chosen.into_iter().collect();
+    }
+
+    let mut outcome = Outcome {
+        stdout: String::new(),
+        stderr: String::new(),
+        status: i32::from(found.is_empty()),
+    };
+    if mode == Mode::Pkill {
+        let mut signalled = 0;
+        for process in &found {
+            match execute_kill_target(process.pid, options.signal) {
+                Ok(()) => {
+                    signalled += 1;
+                    if options.echo {
+                        let _ = writeln!(
+                            outcome.stdout,
+                            "{} killed (pid {})",
+                            process.name, process.pid
+                        );
+                    }
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        outcome.stderr,
+                        "{name}: killing pid {} failed: {e}",
+                        process.pid
+                    );
+                }
+            }
+        }
+        if !found.is_empty() && signalled == 0 {
+            outcome.status = 3;
+        }
+        if options.count {
+            let _ = writeln!(outcome.stdout, "{signalled}");
+        }
+    } else if options.count {
+        let _ = writeln!(outcome.stdout, "{}", found.len());
+    } else {
+        let items: Vec<String> = found
+            .iter()
+            .map(|process| match options.listing {
+                Listing::Pids => process.pid.to_string(),
+                Listing::Names => format!("{} {}", process.pid, process.name),
+                Listing::CommandLines => format!("{} {}", process.pid, process.command_line),
+            })
+            .collect();
+        if !items.is_empty() {
+            outcome.stdout = items.join(&options.delimiter);
+            outcome.stdout.push('\n');
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(mode: Mode, list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(mode, &args)
+    }
+
+    #[test]
+    fn pkill_takes_a_signal_first() {
+        let parsed = options(Mode::Pkill, &["-HUP", "-f", "nginx"]).unwrap();
+        assert_eq!(parsed.signal, 1);
+        assert!(parsed.full);
+        assert_eq!(parsed.pattern.as_deref(), Some("nginx"));
+
+        let parsed = options(Mode::Pkill, &["--signal", "SIGKILL", "-u", "alice,bob"]).unwrap();
+        assert_eq!(parsed.signal, 9);
+        assert_eq!(parsed.users, ["alice", "bob"]);
+
+        // Only the first argument names a signal
+        assert_eq!(
+            options(Mode::Pkill, &["-f", "-9"]).unwrap_err(),
+            "invalid option -- '9'"
+        );
+        assert_eq!(
+            options(Mode::Pgrep, &["-9", "x"]).unwrap_err(),
+            "invalid option -- '9'"
+        );
+    }
+
+    #[test]
+    fn needs_something_to_match() {
+        assert_eq!(
+            options(Mode::Pgrep, &["-l"]).unwrap_err(),
+            "no matching criteria specified"
+        );
+        let parsed = options(Mode::Pgrep, &["-P1", "-d,"]).unwrap();
+        assert_eq!(parsed.parents, [1]);
+        assert_eq!(parsed.delimiter, ",");
+
+        let exact = options(Mode::Pgrep, &["-xi", "SH"]).unwrap();
+        let regex = exact.regex().unwrap().unwrap();
+        assert!(regex.is_match("sh") && !regex.is_match("bash"));
+    }
+}
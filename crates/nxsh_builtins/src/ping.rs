@@ -1,93 +1,661 @@
-//! `ping` builtin - ICMP echo request utility with cross-platform support.
+//! `ping` builtin - send ICMP echo requests to a host
 //!
-//! Delegates to the system `ping` binary when available to provide complete
-//! ICMP functionality. When the binary is unavailable, falls back to a simple
-//! TCP connectivity test for basic network diagnostics.
-
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-
-/// Entry point for the `ping` builtin.
-pub fn ping_cli(args: &[String]) -> Result<()> {
-    // Try platform-specific ping commands
-    let ping_commands = if cfg!(windows) {
-        vec!["ping"]
-    } else {
-        vec!["ping", "ping6"]
-    };
+//! Syntax:
+//!   ping [-46q] [-c COUNT] [-i SECS] [-W SECS] [-t TTL] [-s SIZE] HOST
+//!
+//! Sends an echo request to HOST every second and prints each reply with
+//! its round trip time, or the router that reported HOST unreachable or the
+//! request's time to live exceeded. HOST is an address or a name, which
+//! resolves to its first address of the family `-4` or `-6` asks for.
+//! Without `-c` ping goes on until it is stopped; it then sums up the
+//! requests sent, the replies received and the share lost, with the
+//! minimum, average, maximum and mean deviation of the round trip times.
+//!
+//! Options:
+//!   -4, -6      use IPv4 or IPv6 only
+//!   -c COUNT    stop after COUNT requests
+//!   -i SECS     seconds between requests, at least 0.2 (1 by default)
+//!   -W SECS     seconds to wait for each reply (2 by default)
+//!   -t TTL      time to live of the requests
+//!   -s SIZE     bytes of data in each request (56 by default)
+//!   -q          print only the first line and the summary
+//!
+//! On an interactive terminal a sparkline of the latest round trip times is
+//! kept under the replies, with gaps for the requests that got no reply,
+//! and Ctrl-C stops ping. Otherwise ping stops at the shell's time limit.
+//!
+//! The exit status is 0 when any reply came back, 1 when none did and 2 for
+//! bad options, an unknown HOST or a socket that could not be opened.
 
-    for ping_cmd in ping_commands {
-        if let Ok(path) = which(ping_cmd) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("ping: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
-        }
+use crate::common::icmp::{resolve, Family, ProbeKind, Prober, ReplyKind};
+use crate::common::wait::sleep_unless;
+use crate::common::{io_message, seconds, BuiltinContext, BuiltinResult};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, queue};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Seconds between requests without `-i`
+const DEFAULT_INTERVAL: f64 = 1.0;
+
+/// The shortest interval `-i` allows
+const MIN_INTERVAL: f64 = 0.2;
+
+/// Seconds to wait for a reply without `-W`
+const DEFAULT_WAIT: f64 = 2.0;
+
+/// Bytes of data without `-s`
+const DEFAULT_SIZE: usize = 56;
+
+/// The most data an echo request fits in one IPv4 datagram
+const MAX_SIZE: u64 = 65507;
+
+/// The most round trip times the sparkline shows
+const SPARK_SAMPLES: usize = 60;
+
+/// Width of a screen that has no terminal to measure
+const PLAIN_WIDTH: usize = 80;
+
+/// The `ping` builtin command implementation
+pub struct PingCommand;
+
+impl Builtin for PingCommand {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Send ICMP echo requests to a host"
     }
 
-    // Fallback: basic connectivity test
-    if args.is_empty() {
-        return Err(anyhow!("ping: no host specified"));
+    fn description(&self) -> &'static str {
+        "Send echo requests to a host, print each reply with its round trip time and \
+         sum up the packets lost and the times seen."
     }
 
-    let host = &args[0];
-    println!("PING {host} (TCP connectivity test)");
-    println!("Note: This is a basic connectivity test, not true ICMP ping");
-    println!("Install system ping for full ICMP functionality");
+    fn usage(&self) -> &'static str {
+        "ping [-46q] [-c COUNT] [-i SECS] [-W SECS] [-t TTL] [-s SIZE] HOST"
+    }
 
-    // Simple TCP connectivity test to port 80
-    use std::net::{TcpStream, ToSocketAddrs};
-    use std::time::{Duration, Instant};
+    fn help(&self) -> &'static str {
+        "Send ICMP echo requests to a host. Use 'ping -c 4 example.com' for four \
+         requests or 'ping -6 -i 0.5 ::1' to ping over IPv6 twice a second."
+    }
 
-    let address = format!("{host}:80");
-    let timeout = Duration::from_secs(1);
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(2)
+                    .with_error(format!("ping: {message}\n").into_bytes()))
+            }
+        };
+        let outcome = if ctx.is_interactive() && io::stdout().is_terminal() {
+            let result = Terminal::open(&mut *ctx.stdout)
+                .map_err(|e| io_message(&e))
+                .and_then(|mut screen| ping(&options, &mut screen));
+            Outcome::new(result, Vec::new())
+        } else {
+            let mut screen = Plain {
+                out: Vec::new(),
+                stopped: || ctx.is_timed_out(),
+            };
+            let result = ping(&options, &mut screen);
+            Outcome::new(result, screen.out)
+        };
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
 
-    for i in 1..=4 {
-        let start = Instant::now();
+/// Run ping for the legacy dispatcher, writing to the process's terminal
+/// or standard output
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("ping: {message}");
+            return Ok(2);
+        }
+    };
+    let result = if io::stdout().is_terminal() {
+        Terminal::open(io::stdout())
+            .map_err(|e| io_message(&e))
+            .and_then(|mut screen| ping(&options, &mut screen))
+    } else {
+        let mut screen = Plain {
+            out: io::stdout(),
+            stopped: || false,
+        };
+        ping(&options, &mut screen)
+    };
+    let outcome = Outcome::new(result, Vec::new());
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
 
-        match address.to_socket_addrs() {
-            Ok(mut addrs) => {
-                if let Some(addr) = addrs.next() {
-                    match TcpStream::connect_timeout(&addr, timeout) {
-                        Ok(_) => {
-                            let elapsed = start.elapsed();
-                            println!(
-                                "64 bytes from {}: icmp_seq={} time={:.1}ms (TCP port 80)",
-                                host,
-                                i,
-                                elapsed.as_secs_f64() * 1000.0
-                            );
-                        }
-                        Err(_) => {
-                            println!("From {host}: icmp_seq={i} Destination Host Unreachable");
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn new(result: Result<Stats, String>, stdout: Vec<u8>) -> Self {
+        match result {
+            Ok(stats) => Outcome {
+                stdout,
+                stderr: Vec::new(),
+                status: if stats.received > 0 { 0 } else { 1 },
+            },
+            Err(message) => Outcome {
+                stdout,
+                stderr: format!("ping: {message}\n").into_bytes(),
+                status: 2,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    host: String,
+    family: Family,
+    count: Option<u64>,
+    interval: Duration,
+    wait: Duration,
+    ttl: Option<u8>,
+    size: usize,
+    quiet: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            host: String::new(),
+            family: Family::Any,
+            count: None,
+            interval: Duration::from_secs_f64(DEFAULT_INTERVAL),
+            wait: Duration::from_secs_f64(DEFAULT_WAIT),
+            ttl: None,
+            size: DEFAULT_SIZE,
+            quiet: false,
+        };
+        let mut hosts = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    hosts.extend(iter.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            '4' => options.family = Family::V4,
+                            '6' => options.family = Family::V6,
+                            'q' => options.quiet = true,
+                            'c' | 'i' | 'W' | 't' | 's' => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().map(String::as_str).ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached,
+                                };
+                                options.set(flag, value)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
                         }
                     }
-                } else {
-                    println!("ping: cannot resolve {host}: Unknown host");
-                    break;
                 }
+                _ => hosts.push(arg.clone()),
             }
-            Err(_) => {
-                println!("ping: cannot resolve {host}: Unknown host");
+        }
+        options.host = match hosts.as_slice() {
+            [host] => host.clone(),
+            [] => return Err("missing host operand".to_string()),
+            [_, extra, ..] => return Err(format!("extra operand '{extra}'")),
+        };
+        Ok(options)
+    }
+
+    /// Take the value of an option that has one
+    fn set(&mut self, flag: char, value: &str) -> Result<(), String> {
+        match flag {
+            'c' => self.count = Some(number(value, 1, u64::MAX, "count")?),
+            'i' => {
+                let interval = positive(value, "interval")?;
+                if interval.as_secs_f64() < MIN_INTERVAL {
+                    return Err(format!(
+                        "interval '{value}' is shorter than {MIN_INTERVAL} seconds"
+                    ));
+                }
+                self.interval = interval;
+            }
+            'W' => self.wait = positive(value, "timeout")?,
+            't' => self.ttl = Some(number(value, 1, 255, "TTL")? as u8),
+            _ => self.size = number(value, 0, MAX_SIZE, "packet size")? as usize,
+        }
+        Ok(())
+    }
+}
+
+fn number(text: &str, min: u64, max: u64, what: &str) -> Result<u64, String> {
+    match text.parse::<u64>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("invalid {what} '{text}'")),
+    }
+}
+
+/// A positive number of seconds
+pub(crate) fn positive(text: &str, what: &str) -> Result<Duration, String> {
+    seconds(text)
+        .filter(|secs| !secs.is_zero())
+        .ok_or_else(|| format!("invalid {what} '{text}'"))
+}
+
+/// Where the replies go
+pub(crate) trait Screen {
+    /// Columns available for the status line
+    fn width(&self) -> usize;
+
+    /// Print a line of the report
+    fn line(&mut self, text: &str) -> io::Result<()>;
+
+    /// Show `text` under the report in place of what was there, on a
+    /// terminal only
+    fn status(&mut self, text: &str) -> io::Result<()>;
+
+    /// Wait for up to `timeout`, returning true once the run should stop
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool>;
+}
+
+/// A terminal in raw mode, so that Ctrl-C comes as a key rather than a
+/// signal, with the status line kept at the bottom
+pub(crate) struct Terminal<W: Write> {
+    out: W,
+    interrupted: bool,
+}
+
+impl<W: Write> Terminal<W> {
+    pub(crate) fn open(out: W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Terminal {
+            out,
+            interrupted: false,
+        })
+    }
+
+    /// Clear the status line and return to its start
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(
+            self.out,
+            cursor::MoveToColumn(0),
+            terminal::Clear(ClearType::CurrentLine)
+        )
+    }
+}
+
+impl<W: Write> Drop for Terminal<W> {
+    fn drop(&mut self) {
+        let _ = self.clear().and_then(|()| self.out.flush());
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl<W: Write> Screen for Terminal<W> {
+    fn width(&self) -> usize {
+        terminal::size()
+            .map(|(columns, _)| usize::from(columns))
+            .unwrap_or(PLAIN_WIDTH)
+    }
+
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        self.clear()?;
+        // Raw mode does not return the carriage on a newline
+        write!(self.out, "{text}\r\n")?;
+        self.out.flush()
+    }
+
+    fn status(&mut self, text: &str) -> io::Result<()> {
+        self.clear()?;
+        self.out.write_all(text.as_bytes())?;
+        self.out.flush()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        while !self.interrupted {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(left)? {
                 break;
             }
+            if let Event::Key(key) = event::read()? {
+                self.interrupted =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            }
+        }
+        Ok(self.interrupted)
+    }
+}
+
+/// Lines written one after another when there is no terminal
+pub(crate) struct Plain<W, F> {
+    pub(crate) out: W,
+    pub(crate) stopped: F,
+}
+
+impl<W: Write, F: Fn() -> bool> Screen for Plain<W, F> {
+    fn width(&self) -> usize {
+        PLAIN_WIDTH
+    }
+
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        writeln!(self.out, "{text}")
+    }
+
+    fn status(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        Ok(sleep_unless(timeout, &self.stopped))
+    }
+}
+
+/// The requests sent and what came back
+#[derive(Debug, Default)]
+struct Stats {
+    sent: u64,
+    received: u64,
+    /// Replies reporting an error, and requests that could not be sent
+    errors: u64,
+    /// Round trip times of the replies, in milliseconds
+    times: Vec<f64>,
+    /// The latest round trip times, None for each request without a reply
+    recent: VecDeque<Option<f64>>,
+}
+
+impl Stats {
+    fn record(&mut self, time: Option<f64>) {
+        if let Some(time) = time {
+            self.received += 1;
+            self.times.push(time);
+        }
+        self.recent.push_back(time);
+        if self.recent.len() > SPARK_SAMPLES {
+            self.recent.pop_front();
         }
+    }
 
-        if i < 4 {
-            std::thread::sleep(Duration::from_secs(1));
+    /// The share of requests lost, in percent to one decimal when it is not
+    /// a whole number
+    fn loss(&self) -> String {
+        if self.sent == 0 {
+            return "0".to_string();
+        }
+        let tenths = (self.sent - self.received) * 1000 / self.sent;
+        match tenths % 10 {
+            0 => (tenths / 10).to_string(),
+            tenth => format!("{}.{tenth}", tenths / 10),
         }
     }
 
-    Ok(())
+    fn summary(&self, host: &str, elapsed: Duration) -> Vec<String> {
+        let mut counts = format!(
+            "{} packets transmitted, {} received",
+            self.sent, self.received
+        );
+        if self.errors > 0 {
+            let _ = write!(counts, ", +{} errors", self.errors);
+        }
+        let _ = write!(
+            counts,
+            ", {}% packet loss, time {}ms",
+            self.loss(),
+            elapsed.as_millis()
+        );
+        let mut lines = vec![
+            String::new(),
+            format!("--- {host} ping statistics ---"),
+            counts,
+        ];
+        if !self.times.is_empty() {
+            let count = self.times.len() as f64;
+            let min = self.times.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = self.times.iter().copied().fold(0.0, f64::max);
+            let avg = self.times.iter().sum::<f64>() / count;
+            let squares = self.times.iter().map(|time| time * time).sum::<f64>() / count;
+            let mdev = (squares - avg * avg).max(0.0).sqrt();
+            lines.push(format!(
+                "rtt min/avg/max/mdev = {min:.3}/{avg:.3}/{max:.3}/{mdev:.3} ms"
+            ));
+        }
+        lines
+    }
 }
 
-/// Execute function stub
-pub fn execute(
-    _args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+/// Resolve the host and open the socket, then send the requests
+fn ping(options: &Options, screen: &mut dyn Screen) -> Result<Stats, String> {
+    let target = resolve(&options.host, options.family)?;
+    let mut prober =
+        Prober::open(target, ProbeKind::Echo).map_err(|e| format!("socket: {}", io_message(&e)))?;
+    let header = if target.is_ipv6() { 40 } else { 20 };
+    let first = format!(
+        "PING {} ({target}) {}({}) bytes of data.",
+        options.host,
+        options.size,
+        options.size + 8 + header
+    );
+    report(options, &first, &mut prober, screen).map_err(|e| io_message(&e))
+}
+
+/// Send the requests until the count is reached or the screen says to stop
+fn report(
+    options: &Options,
+    first: &str,
+    prober: &mut Prober,
+    screen: &mut dyn Screen,
+) -> io::Result<Stats> {
+    screen.line(first)?;
+    let started = Instant::now();
+    let mut stats = Stats::default();
+    let mut seq: u16 = 0;
+    loop {
+        seq = seq.wrapping_add(1);
+        stats.sent += 1;
+        let sent = Instant::now();
+        let reply = prober.probe(seq, options.ttl, options.size, options.wait, &mut || {
+            screen.wait(Duration::ZERO).unwrap_or(true)
+        });
+        let line = match reply {
+            Ok(Some(reply)) if reply.kind == ReplyKind::Echo => {
+                let time = reply.rtt.as_secs_f64() * 1000.0;
+                stats.record(Some(time));
+                let ttl = reply
+                    .ttl
+                    .map(|ttl| format!(" ttl={ttl}"))
+                    .unwrap_or_default();
+                Some(format!(
+                    "{} bytes from {}: icmp_seq={seq}{ttl} time={} ms",
+                    reply.size,
+                    reply.from,
+                    millis(time)
+                ))
+            }
+            Ok(Some(reply)) => {
+                stats.record(None);
+                stats.errors += 1;
+                Some(format!("From {} icmp_seq={seq} {}", reply.from, reply.kind))
+            }
+            Ok(None) => {
+                stats.record(None);
+                // A request cut short by Ctrl-C did not time out
+                (!screen.wait(Duration::ZERO)?)
+                    .then(|| format!("Request timeout for icmp_seq {seq}"))
+            }
+            Err(e) => {
+                stats.record(None);
+                stats.errors += 1;
+                Some(format!("icmp_seq={seq}: {}", io_message(&e)))
+            }
+        };
+        if let Some(line) = line.filter(|_| !options.quiet) {
+            screen.line(&line)?;
+        }
+        screen.status(&chart(&stats, screen.width()))?;
+        if options.count.is_some_and(|count| stats.sent >= count) {
+            break;
+        }
+        let left = (sent + options.interval).saturating_duration_since(Instant::now());
+        if screen.wait(left)? {
+            break;
+        }
+    }
+    for line in stats.summary(&options.host, started.elapsed()) {
+        screen.line(&line)?;
+    }
+    Ok(stats)
+}
+
+/// A round trip time with fewer decimals the longer it is
+fn millis(time: f64) -> String {
+    match time {
+        time if time >= 100.0 => format!("{time:.0}"),
+        time if time >= 10.0 => format!("{time:.1}"),
+        time if time >= 1.0 => format!("{time:.2}"),
+        time => format!("{time:.3}"),
+    }
+}
+
+/// Bars for round trip times, scaled to the slowest, with a gap for each
+/// request that got no reply
+fn sparkline(times: &[Option<f64>]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let slowest = times.iter().flatten().copied().fold(0.0, f64::max);
+    times
+        .iter()
+        .map(|time| match time {
+            Some(time) if slowest > 0.0 => {
+                let height = (time / slowest * BARS.len() as f64).ceil() as usize;
+                BARS[height.clamp(1, BARS.len()) - 1]
+            }
+            Some(_) => BARS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+/// The status line: as many of the latest times as fit in `width` columns
+/// next to the last time and the loss so far
+fn chart(stats: &Stats, width: usize) -> String {
+    let last = match stats.recent.back() {
+        Some(Some(time)) => format!("{} ms", millis(*time)),
+        _ => "no reply".to_string(),
+    };
+    let text = format!(
+        " {last}, {}/{} received, {}% loss",
+        stats.received,
+        stats.sent,
+        stats.loss()
+    );
+    let room = width.saturating_sub(text.chars().count() + 1);
+    let skip = stats.recent.len().saturating_sub(room);
+    let times: Vec<Option<f64>> = stats.recent.iter().skip(skip).copied().collect();
+    sparkline(&times) + &text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parses_counts_intervals_and_families() {
+        let parsed = options(&["-6qc", "3", "-i0.5", "-W", "1", "-t64", "::1"]).unwrap();
+        assert_eq!(parsed.family, Family::V6);
+        assert!(parsed.quiet);
+        assert_eq!(parsed.count, Some(3));
+        assert_eq!(parsed.interval, Duration::from_millis(500));
+        assert_eq!(parsed.wait, Duration::from_secs(1));
+        assert_eq!(parsed.ttl, Some(64));
+        assert_eq!(parsed.size, DEFAULT_SIZE);
+        assert_eq!(parsed.host, "::1");
+
+        assert_eq!(options(&[]).unwrap_err(), "missing host operand");
+        assert_eq!(
+            options(&["-c"]).unwrap_err(),
+            "option requires an argument -- 'c'"
+        );
+        assert_eq!(options(&["-c0", "a"]).unwrap_err(), "invalid count '0'");
+        assert_eq!(
+            options(&["-t", "256", "a"]).unwrap_err(),
+            "invalid TTL '256'"
+        );
+        assert_eq!(
+            options(&["-i", "0.01", "a"]).unwrap_err(),
+            "interval '0.01' is shorter than 0.2 seconds"
+        );
+        assert_eq!(options(&["a", "b"]).unwrap_err(), "extra operand 'b'");
+    }
+
+    #[test]
+    fn sums_up_loss_and_round_trip_times() {
+        let mut stats = Stats::default();
+        for time in [Some(10.0), None, Some(20.0)] {
+            stats.sent += 1;
+            stats.record(time);
+        }
+        assert_eq!(stats.loss(), "33.3");
+        assert_eq!(
+            stats.summary("host", Duration::from_millis(2003)),
+            [
+                "",
+                "--- host ping statistics ---",
+                "3 packets transmitted, 2 received, 33.3% packet loss, time 2003ms",
+                "rtt min/avg/max/mdev = 10.000/15.000/20.000/5.000 ms",
+            ]
+        );
+        stats.sent += 1;
+        stats.errors += 1;
+        stats.record(None);
+        assert_eq!(
+            stats.summary("host", Duration::ZERO)[2],
+            "4 packets transmitted, 2 received, +1 errors, 50% packet loss, time 0ms"
+        );
+    }
+
+    #[test]
+    fn draws_sparklines_that_fit() {
+        assert_eq!(
+            sparkline(&[Some(1.0), Some(4.0), None, Some(8.0), Some(0.0)]),
+            "▁▄ █▁"
+        );
+        assert_eq!(sparkline(&[Some(0.0)]), "▁");
+        assert_eq!(millis(0.0456), "0.046");
+        assert_eq!(millis(23.46), "23.5");
+
+        let mut stats = Stats::default();
+        for time in [Some(2.0), Some(4.0), None, Some(8.0)] {
+            stats.sent += 1;
+            stats.record(time);
+        }
+        assert_eq!(chart(&stats, 80), "▂▄ █ 8.00 ms, 3/4 received, 25% loss");
+        assert_eq!(chart(&stats, 34), "█ 8.00 ms, 3/4 received, 25% loss");
+    }
 }
@@ -0,0 +1,888 @@
+//! `plugin` builtin - install and manage shell plugins
+//!
+//! Syntax:
+//!   plugin install [-k KEY] [--insecure-allow-revoked] SOURCE
+//!   plugin list
+//!   plugin enable [--insecure-allow-revoked] NAME
+//!   plugin disable NAME
+//!   plugin update [-f] [--insecure-allow-revoked] NAME [SOURCE]
+//!   plugin remove NAME
+//!   plugin dev [--stop] [PATH]
+//!   plugin permissions [--reset] [NAME]
+//!   plugin verify PATH
+//!   plugin keys [list]
+//!   plugin keys add [-t LEVEL] ID KEY
+//!   plugin keys remove ID
+//!   plugin keys trust-level ID [LEVEL]
+//!
+//! `install` copies the plugin file at SOURCE, a path or an `http(s)` URL,
+//! into the plugin directory (`$NXSH_PLUGIN_DIR`, or `plugins` in the
+//! configuration directory) under the name its manifest gives, along with
+//! the detached signature `NAME.sig` next to it if there is one. `list`
+//! shows each installed plugin with its version, whether it is enabled and
+//! what its signature shows. `disable` keeps a plugin installed without
+//! loading it, and `enable` loads it again. `update` replaces a plugin with
+//! a newer version from SOURCE, by default where it was installed from;
+//! the replaced file is kept in the plugin cache. `remove` uninstalls a
+//! plugin with its signature, cached files and pinned key.
+//!
+//! Installed and enabled plugins are loaded when the shell starts; the
+//! commands of a plugin that is disabled or removed go away at once, along
+//! with the hooks its manifest runs them on (see the `hook` builtin), its
+//! completion of command arguments and its prompt segments, which prompt
+//! templates show as `{plugin:NAME}`.
+//!
+//! `dev` loads the plugin built at PATH, a `.wasm` module or a native
+//! `.so`, `.dylib` or `.dll` library, in place of an installed plugin of the
+//! same name, and reloads it in the background whenever the build rewrites
+//! PATH: commands already running finish in the old version, its commands
+//! are replaced by those of the new one, and each reload is reported on
+//! standard error. `dev --stop PATH` unloads the plugin again, and `dev`
+//! alone lists the plugins being developed.
+//!
+//! A plugin gets only the capabilities of its manifest the user grants.
+//! The shell asks about each capability the first time a version of a
+//! plugin is loaded and records the answers in `plugin-permissions.toml`
+//! in the configuration directory. A capability that cannot be asked
+//! about, without a terminal or when a plugin in development is reloaded
+//! in the background, is denied until the plugin is next loaded.
+//! `permissions` shows what was granted and denied, to plugin NAME or to
+//! all plugins, and `permissions --reset NAME` forgets it so that the
+//! next load asks again.
+//!
+//! `verify` checks that the plugin built at PATH is one the shell can load,
+//! without loading it: for a `.wasm` module its manifest and metadata, the
+//! capabilities it asks for, the WASI functions it imports and the
+//! signatures of the functions it exports; for a native library the
+//! functions it must export. Each check is shown as `ok`, `warn` for what
+//! loads but will not work as expected, or `FAIL`, and the exit status is
+//! 1 when a check fails.
+//!
+//! Options:
+//!   -k, --key KEY   pin the base64 Ed25519 public key KEY for the plugin
+//!   -f, --force     update even to the same or an older version
+//!   -s, --stop      stop reloading the plugin at PATH and unload it
+//!   -r, --reset     forget the permissions granted to and denied NAME
+//!   -t, --trust LEVEL
+//!                   trust the key added at LEVEL, by default `signer`
+//!   --insecure-allow-revoked
+//!                   install, update or enable NAME and load it even if it
+//!                   is signed with a revoked key
+//!
+//! Signatures are checked against the pinned key and the keys of the
+//! keyring, `keyring.toml` in the plugin directory; a plugin whose signature
+//! does not verify is not installed. `keys` manages the keyring: `add`
+//! trusts the base64 Ed25519 public key KEY as ID, `remove` forgets it,
+//! `trust-level` shows or changes how far ID is trusted and `list` shows
+//! each key with its level and fingerprint. A `root` key signs plugins and
+//! certifies intermediate signing keys, whose certificates a signature
+//! carries; a `signer` key only signs plugins, and an `untrusted` key is not
+//! used. The official and community keys are built in as root keys.
+//!
+//! Keys are revoked in a signed revocation list, fetched at most daily from
+//! `$NXSH_PLUGIN_REVOCATION_URL`, or the official list when the official
+//! key is configured, and used from the cache for a week when it cannot be
+//! fetched. A plugin signed with a revoked key, or whose keys cannot be
+//! checked against a recent enough list, is neither installed nor loaded
+//! unless it was installed, updated or enabled with `--insecure-allow-revoked`.
+//!
+//! The exit status is 0 on success, 1 when an operation fails and 2 on
+//! usage errors.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::hooks::{HookEvent, HookKind, HookRegistry};
+use nxsh_core::{
+    Builtin, CompletionSpec, ExecutionResult, PluginCommand, PluginCommandRegistry,
+    PluginCompleter, PromptSegment, ShellResult,
+};
+use nxsh_plugin::consent::{
+    describe_capability, CapabilityConsent, ConsentPrompt, ConsentRequest, Decision,
+    PermissionGrants,
+};
+use nxsh_plugin::dev::DevSession;
+use nxsh_plugin::store::PluginStore;
+use nxsh_plugin::trust::{Keyring, TrustLevel};
+use nxsh_plugin::PluginManager;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{JoinHandle, ThreadId};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+const USAGE: &str = "plugin install [-k KEY] [--insecure-allow-revoked] SOURCE | plugin list | plugin enable [--insecure-allow-revoked] NAME | plugin disable NAME | plugin update [-f] [--insecure-allow-revoked] NAME [SOURCE] | plugin remove NAME | plugin dev [--stop] [PATH] | plugin permissions [--reset] [NAME] | plugin verify PATH | plugin keys [list | add [-t LEVEL] ID KEY | remove ID | trust-level ID [LEVEL]]";
+
+/// Status of a plugin command that could not be run
+const CANNOT_RUN: i32 = 126;
+
+/// How long a development watcher waits for a change before checking
+/// whether it has been stopped
+const STOP_CHECK: Duration = Duration::from_millis(200);
+
+/// Drives the asynchronous plugin system from the synchronous shell
+static RUNTIME: Lazy<Option<Runtime>> = Lazy::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .map_err(|e| eprintln!("nxsh: plugins: {e}"))
+        .ok()
+});
+
+/// Plugins being developed with `plugin dev`, by artifact
+static DEV_WATCHERS: Lazy<Mutex<BTreeMap<PathBuf, DevWatcher>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// The background thread reloading a plugin in development
+struct DevWatcher {
+    /// ID of the plugin loaded from the artifact
+    plugin_id: Arc<Mutex<String>>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// The `plugin` builtin command implementation
+pub struct PluginManagerCommand;
+
+impl Builtin for PluginManagerCommand {
+    fn name(&self) -> &'static str {
+        "plugin"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Install and manage shell plugins"
+    }
+
+    fn description(&self) -> &'static str {
+        "Install plugins from a path or URL, list them with their version and signature \
+         status, enable or disable them without uninstalling, update them to newer versions \
+         and remove them, develop one with it reloaded whenever it is rebuilt, review \
+         the capabilities granted to them, check that one conforms to the plugin ABI, \
+         or manage the keys trusted to sign them."
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Manage plugins. Use 'plugin install ./greeter.wasm' to install a plugin for the \
+         next shell, 'plugin list' to see what is installed, 'plugin disable greeter' \
+         to stop loading it and 'plugin dev target/greeter.wasm' to try out a build."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let host = PluginHost {
+            commands: ctx.plugin_commands.clone(),
+            hooks: ctx.hooks.clone(),
+        };
+        let outcome = run(args, &ctx.cwd, Some(&host));
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run plugin for the legacy dispatcher
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, None);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Where a running shell keeps the commands and hooks of loaded plugins
+#[derive(Debug, Clone, Default)]
+pub struct PluginHost {
+    pub commands: PluginCommandRegistry,
+    pub hooks: HookRegistry,
+}
+
+impl PluginHost {
+    /// Register the commands of the loaded plugin `plugin`, or of all
+    /// loaded plugins, with their help and completion spec, the hooks
+    /// their commands are run on, and their completers and prompt segments
+    fn register(&self, plugin: Option<&str>) {
+        let Some(runtime) = RUNTIME.as_ref() else {
+            return;
+        };
+        for (id, spec) in runtime.block_on(nxsh_plugin::plugin_completers()) {
+            if plugin.is_some_and(|plugin| plugin != id) {
+                continue;
+            }
+            let command = spec.command.clone();
+            self.commands.register_completer(PluginCompleter {
+                command: spec.command,
+                plugin: id,
+                complete: Arc::new(move |line: &str, cursor: usize| {
+                    complete(&command, line, cursor)
+                }),
+            });
+        }
+        for (id, spec) in runtime.block_on(nxsh_plugin::plugin_segments()) {
+            if plugin.is_some_and(|plugin| plugin != id) {
+                continue;
+            }
+            let name = spec.name.clone();
+            self.commands.register_segment(PromptSegment {
+                name: spec.name,
+                plugin: id,
+                timeout: spec.timeout_ms.map(Duration::from_millis),
+                render: Arc::new(move |cwd: &Path| render_segment(&name, cwd)),
+            });
+        }
+        for (id, spec) in runtime.block_on(nxsh_plugin::plugin_commands()) {
+            if plugin.is_some_and(|plugin| plugin != id) {
+                continue;
+            }
+            let name = spec.name.clone();
+            for event in &spec.hooks {
+                let Some(kind) = HookKind::parse(event) else {
+                    eprintln!("nxsh: plugin {id}: {name}: no such event {event}");
+                    continue;
+                };
+                let command = name.clone();
+                self.hooks.add_callback(
+                    kind,
+                    &id,
+                    &name,
+                    Arc::new(move |event: &HookEvent, ctx: &mut ShellContext| {
+                        let args: Vec<String> =
+                            event.values().into_iter().map(|(_, value)| value).collect();
+                        run_command(&command, ctx, &args)
+                    }),
+                );
+            }
+            let synopsis = if spec.synopsis.is_empty() {
+                format!("Run {name} from plugin {id}")
+            } else {
+                spec.synopsis
+            };
+            let usage = if spec.usage.is_empty() {
+                format!("{name} [ARG]...")
+            } else {
+                spec.usage
+            };
+            self.commands.register(PluginCommand {
+                name: name.clone(),
+                plugin: id,
+                synopsis,
+                help: spec.help,
+                completion: CompletionSpec {
+                    usage,
+                    flags: spec.flags,
+                },
+                handler: Arc::new(move |ctx, args| run_command(&name, ctx, args)),
+            });
+        }
+    }
+
+    /// Remove the commands and hooks of plugin `id`
+    fn unregister(&self, id: &str) {
+        self.commands.unregister_plugin(id);
+        self.hooks.remove_owner(id);
+    }
+}
+
+/// Load the installed and enabled plugins into the plugin system and
+/// register their commands and hooks in `host`, reporting plugins that
+/// fail to load and skipping them. Plugins loaded from now on get the
+/// capabilities the user grants them.
+pub fn load(host: &PluginHost) {
+    let Some(runtime) = RUNTIME.as_ref() else {
+        return;
+    };
+    runtime.block_on(async {
+        if let Err(e) = nxsh_plugin::initialize().await {
+            eprintln!("nxsh: plugins: {e:#}");
+            return;
+        }
+        let consent = PermissionGrants::open().map(|grants| {
+            let prompt = TerminalPrompt {
+                thread: std::thread::current().id(),
+            };
+            CapabilityConsent::new(grants, Some(Box::new(prompt)))
+        });
+        let consented = match consent {
+            Ok(consent) => nxsh_plugin::set_consent(consent).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = consented {
+            // Plugins are not loaded with capabilities nobody consented to
+            eprintln!("nxsh: plugins: {e:#}");
+            return;
+        }
+        let listed = PluginStore::open().and_then(|store| Ok((store.startup_files()?, store)));
+        let (files, store) = match listed {
+            Ok(listed) => listed,
+            Err(e) => {
+                eprintln!("nxsh: plugins: {e:#}");
+                return;
+            }
+        };
+        for path in files {
+            // Plugins signed with revoked keys stay unloaded
+            let loaded = match store.check_revoked(&path) {
+                Ok(()) => nxsh_plugin::load_plugin(&path).await.map(drop),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = loaded {
+                eprintln!("nxsh: plugin {}: {e:#}", path.display());
+            }
+        }
+    });
+    host.register(None);
+}
+
+/// Asks the user on the terminal about the capabilities of plugins loaded
+/// on the shell's thread, and nobody about those of plugins reloaded in the
+/// background
+struct TerminalPrompt {
+    thread: ThreadId,
+}
+
+impl ConsentPrompt for TerminalPrompt {
+    fn ask(&self, request: &ConsentRequest<'_>) -> Option<Vec<Decision>> {
+        let plugin = format!("{} {}", request.name, request.version);
+        let unanswered = |why: &str| {
+            eprintln!(
+                "nxsh: plugin {plugin} runs without {}: {why}",
+                request.capabilities.join(", ")
+            );
+            None
+        };
+        if std::thread::current().id() != self.thread {
+            return unanswered("it was reloaded in the background");
+        }
+        let dialog = nxsh_ui::consent::ConsentRequest {
+            requester: format!("plugin {plugin}"),
+            description: request.description.to_string(),
+            permissions: request
+                .capabilities
+                .iter()
+                .map(|capability| nxsh_ui::consent::Permission {
+                    name: capability.clone(),
+                    summary: describe_capability(capability),
+                })
+                .collect(),
+        };
+        match nxsh_ui::consent::ask(&dialog, &mut io::stderr()) {
+            Ok(Some(granted)) => Some(
+                granted
+                    .into_iter()
+                    .map(|yes| {
+                        if yes {
+                            Decision::Granted
+                        } else {
+                            Decision::Denied
+                        }
+                    })
+                    .collect(),
+            ),
+            Ok(None) => unanswered("there is no terminal to ask for permission on"),
+            Err(e) => unanswered(&e.to_string()),
+        }
+    }
+}
+
+/// Run the plugin command `name` with the shell's environment and directory
+fn run_command(
+    name: &str,
+    ctx: &mut ShellContext,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let env: HashMap<String, String> = ctx
+        .env
+        .read()
+        .map(|env| env.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .unwrap_or_default();
+    let output = match RUNTIME.as_ref() {
+        Some(runtime) => runtime
+            .block_on(nxsh_plugin::run_command(name, args, &env, &ctx.cwd))
+            .map_err(|e| format!("{e:#}")),
+        None => Err("plugin runtime unavailable".to_string()),
+    };
+    Ok(match output {
+        Ok(output) => ExecutionResult::success(output.status)
+            .with_output(output.stdout)
+            .with_error(output.stderr),
+        Err(message) => ExecutionResult::success(CANNOT_RUN)
+            .with_error(format!("{name}: {message}\n").into_bytes()),
+    })
+}
+
+/// Candidates for the word under the cursor from the completer a plugin
+/// provides for `command`; none when it fails
+fn complete(command: &str, line: &str, cursor: usize) -> Vec<String> {
+    let Some(runtime) = RUNTIME.as_ref() else {
+        return Vec::new();
+    };
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    runtime
+        .block_on(nxsh_plugin::complete(command, line, cursor, &env, &cwd))
+        .unwrap_or_default()
+}
+
+/// Text of the plugin prompt segment `name` in `cwd`; empty when it fails
+fn render_segment(name: &str, cwd: &Path) -> String {
+    let Some(runtime) = RUNTIME.as_ref() else {
+        return String::new();
+    };
+    let env: HashMap<String, String> = std::env::vars().collect();
+    runtime
+        .block_on(nxsh_plugin::render_segment(name, &env, cwd))
+        .unwrap_or_default()
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn printed(text: String) -> Self {
+        Outcome {
+            stdout: text.into_bytes(),
+            stderr: Vec::new(),
+            status: 0,
+        }
+    }
+
+    fn failure(error: anyhow::Error) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("plugin: {error:#}\n").into_bytes(),
+            status: 1,
+        }
+    }
+
+    fn usage(message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("plugin: {message}\nplugin: usage: {USAGE}\n").into_bytes(),
+            status: 2,
+        }
+    }
+}
+
+/// The options and operands of a subcommand
+#[derive(Default)]
+struct Options {
+    key: Option<String>,
+    force: bool,
+    stop: bool,
+    reset: bool,
+    trust: Option<String>,
+    allow_revoked: bool,
+    operands: Vec<String>,
+}
+
+/// Split `args` into options, with the values of `-k`/`--key` and
+/// `-t`/`--trust`, and operands
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-k" | "--key" => match args.next() {
+                Some(value) => options.key = Some(value.clone()),
+                None => return Err(format!("{arg} requires a key")),
+            },
+            "-f" | "--force" => options.force = true,
+            "-s" | "--stop" => options.stop = true,
+            "-r" | "--reset" => options.reset = true,
+            "-t" | "--trust" => match args.next() {
+                Some(value) => options.trust = Some(value.clone()),
+                None => return Err(format!("{arg} requires a trust level")),
+            },
+            "--insecure-allow-revoked" => options.allow_revoked = true,
+            "--" => {
+                options.operands.extend(args.by_ref().cloned());
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Err(format!("{option}: unknown option"));
+            }
+            _ => options.operands.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+/// SOURCE as the store takes it: URLs as given, paths from `cwd`
+fn resolve_source(source: &str, cwd: &Path) -> String {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        source.to_string()
+    } else {
+        cwd.join(source).to_string_lossy().into_owned()
+    }
+}
+
+fn run(args: &[String], cwd: &Path, host: Option<&PluginHost>) -> Outcome {
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Outcome::usage("a subcommand is required".to_string());
+    };
+    let Options {
+        key,
+        force,
+        stop,
+        reset,
+        trust,
+        allow_revoked,
+        operands,
+    } = match parse_options(rest) {
+        Ok(options) => options,
+        Err(message) => return Outcome::usage(message),
+    };
+    if key.is_some() && subcommand != "install" {
+        return Outcome::usage("--key is only for install".to_string());
+    }
+    if force && subcommand != "update" {
+        return Outcome::usage("--force is only for update".to_string());
+    }
+    if stop && subcommand != "dev" {
+        return Outcome::usage("--stop is only for dev".to_string());
+    }
+    if reset && subcommand != "permissions" {
+        return Outcome::usage("--reset is only for permissions".to_string());
+    }
+    if trust.is_some() && !(subcommand == "keys" && operands.first().is_some_and(|o| o == "add")) {
+        return Outcome::usage("--trust is only for keys add".to_string());
+    }
+    if allow_revoked && !matches!(subcommand.as_str(), "install" | "update" | "enable") {
+        return Outcome::usage(
+            "--insecure-allow-revoked is only for install, update and enable".to_string(),
+        );
+    }
+    if subcommand == "permissions" {
+        return match (operands.as_slice(), reset) {
+            ([], false) => permissions(None),
+            ([name], false) => permissions(Some(name)),
+            ([name], true) => reset_permissions(name),
+            _ => Outcome::usage("wrong number of operands for permissions".to_string()),
+        };
+    }
+    if subcommand == "dev" {
+        return match (operands.as_slice(), stop) {
+            ([], false) => Outcome::printed(list_dev()),
+            ([path], false) => match host {
+                Some(host) => start_dev(&cwd.join(path), host),
+                None => Outcome::failure(anyhow::anyhow!("dev needs a running shell")),
+            },
+            ([path], true) => stop_dev(&cwd.join(path)),
+            _ => Outcome::usage("wrong number of operands for dev".to_string()),
+        };
+    }
+    if subcommand == "verify" {
+        return match operands.as_slice() {
+            [path] => verify(&cwd.join(path), path),
+            _ => Outcome::usage("wrong number of operands for verify".to_string()),
+        };
+    }
+    let store = match PluginStore::open() {
+        Ok(store) => store.allow_revoked(allow_revoked),
+        Err(error) => return Outcome::failure(error),
+    };
+    if subcommand == "keys" {
+        return keys(store.keyring(), &operands, trust.as_deref());
+    }
+    let manager = PluginManager::new();
+    // Commands and hooks of a plugin that stops being loaded leave the
+    // running shell
+    let unregister = |id: String| {
+        if let Some(host) = host {
+            host.unregister(&id);
+        }
+    };
+    let result = match (subcommand.as_str(), operands.as_slice()) {
+        ("install", [source]) => store
+            .install(&manager, &resolve_source(source, cwd), key.as_deref())
+            .map(|plugin| format!("installed {} {}\n", plugin.name, plugin.version)),
+        ("list", []) => store.list().map(|plugins| {
+            let name_width = plugins.iter().map(|p| p.name.len()).max().unwrap_or(0);
+            let version_width = plugins.iter().map(|p| p.version.len()).max().unwrap_or(0);
+            plugins
+                .iter()
+                .map(|plugin| {
+                    let state = if plugin.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    };
+                    format!(
+                        "{:name_width$}  {:version_width$}  {state:8}  {}\n",
+                        plugin.name,
+                        plugin.version,
+                        store.signature_status(plugin)
+                    )
+                })
+                .collect()
+        }),
+        ("enable", [name]) => store.set_enabled(name, true).map(|_| String::new()),
+        ("disable", [name]) => store.set_enabled(name, false).map(|plugin| {
+            unregister(plugin.id());
+            String::new()
+        }),
+        ("update", [name]) | ("update", [name, _]) => {
+            let source = operands.get(1).map(|source| resolve_source(source, cwd));
+            store
+                .update(&manager, name, source.as_deref(), force)
+                .map(|(old, plugin)| {
+                    if old == plugin.version && !force {
+                        format!("{name} {old} is up to date\n")
+                    } else {
+                        unregister(format!("{name}@{old}"));
+                        format!("updated {name} {old} -> {}\n", plugin.version)
+                    }
+                })
+        }
+        ("remove", [name]) => store.remove(name).map(|plugin| {
+            unregister(plugin.id());
+            format!("removed {} {}\n", plugin.name, plugin.version)
+        }),
+        ("install" | "list" | "enable" | "disable" | "update" | "remove", _) => {
+            return Outcome::usage(format!("wrong number of operands for {subcommand}"));
+        }
+        (other, _) => return Outcome::usage(format!("{other}: unknown subcommand")),
+    };
+    match result {
+        Ok(text) => Outcome::printed(text),
+        Err(error) => Outcome::failure(error),
+    }
+}
+
+/// `plugin keys`: list the keys trusted to sign plugins, add and remove
+/// keys and change how far they are trusted
+fn keys(keyring: &Keyring, operands: &[String], trust: Option<&str>) -> Outcome {
+    let parse_level = |name: &str| {
+        TrustLevel::parse(name).ok_or_else(|| {
+            Outcome::usage(format!(
+                "{name}: unknown trust level; use root, signer or untrusted"
+            ))
+        })
+    };
+    let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+    let result = match operands.as_slice() {
+        [] | ["list"] => keyring.list().map(|keys| {
+            let id_width = keys.iter().map(|key| key.id.len()).max().unwrap_or(0);
+            keys.iter()
+                .map(|key| {
+                    let origin = if key.builtin { "  built in" } else { "" };
+                    format!(
+                        "{:id_width$}  {:9}  {}{origin}\n",
+                        key.id,
+                        key.level.name(),
+                        key.fingerprint()
+                    )
+                })
+                .collect()
+        }),
+        ["add", id, key] => {
+            let level = match trust.map(parse_level).transpose() {
+                Ok(level) => level.unwrap_or(TrustLevel::Signer),
+                Err(outcome) => return outcome,
+            };
+            keyring
+                .add(id, key, level)
+                .map(|key| format!("added {} as a {} key\n", key.id, key.level))
+        }
+        ["remove", id] => keyring
+            .remove(id)
+            .map(|key| format!("removed {}\n", key.id)),
+        ["trust-level", id] => keyring.get(id).map(|key| format!("{}\n", key.level)),
+        ["trust-level", id, level] => match parse_level(level) {
+            Ok(level) => keyring.set_level(id, level).map(|_| String::new()),
+            Err(outcome) => return outcome,
+        },
+        [action @ ("list" | "add" | "remove" | "trust-level"), ..] => {
+            return Outcome::usage(format!("wrong number of operands for keys {action}"));
+        }
+        [other, ..] => return Outcome::usage(format!("keys {other}: unknown subcommand")),
+    };
+    match result {
+        Ok(text) => Outcome::printed(text),
+        Err(error) => Outcome::failure(error),
+    }
+}
+
+/// The capabilities granted to and denied plugin `name`, or every plugin,
+/// a line for each with the version it was decided for
+fn permissions(name: Option<&str>) -> Outcome {
+    let recorded = match PermissionGrants::open().and_then(|grants| grants.list()) {
+        Ok(recorded) => recorded,
+        Err(error) => return Outcome::failure(error),
+    };
+    let mut rows = Vec::new();
+    for (id, grants) in &recorded {
+        let plugin = id
+            .rsplit_once('@')
+            .map_or(id.as_str(), |(plugin, _)| plugin);
+        if name.is_some_and(|name| name != plugin) {
+            continue;
+        }
+        rows.extend(grants.granted.iter().map(|c| (id, c, "granted")));
+        rows.extend(grants.denied.iter().map(|c| (id, c, "denied")));
+    }
+    let id_width = rows.iter().map(|(id, ..)| id.len()).max().unwrap_or(0);
+    let capability_width = rows.iter().map(|(_, c, _)| c.len()).max().unwrap_or(0);
+    Outcome::printed(
+        rows.iter()
+            .map(|(id, capability, decision)| {
+                format!("{id:id_width$}  {capability:capability_width$}  {decision}\n")
+            })
+            .collect(),
+    )
+}
+
+/// Forget the capabilities granted to and denied plugin `name`
+fn reset_permissions(name: &str) -> Outcome {
+    match PermissionGrants::open().and_then(|grants| grants.forget(name)) {
+        Ok(0) => Outcome::failure(anyhow::anyhow!("no permissions are recorded for {name}")),
+        Ok(_) => Outcome::printed(format!("forgot the permissions of {name}\n")),
+        Err(error) => Outcome::failure(error),
+    }
+}
+
+/// Run the conformance checks on the plugin at `path`, shown as `shown`
+fn verify(path: &Path, shown: &str) -> Outcome {
+    match nxsh_plugin::testing::verify(path) {
+        Ok(report) if report.passed() => Outcome::printed(report.to_string()),
+        Ok(report) => Outcome {
+            stdout: report.to_string().into_bytes(),
+            stderr: format!("plugin: {shown} does not conform\n").into_bytes(),
+            status: 1,
+        },
+        Err(error) => Outcome::failure(error),
+    }
+}
+
+/// The artifacts being developed, each with the plugin loaded from it
+fn list_dev() -> String {
+    let watchers = DEV_WATCHERS.lock().unwrap_or_else(|e| e.into_inner());
+    watchers
+        .iter()
+        .map(|(artifact, watcher)| {
+            let plugin_id = watcher.plugin_id.lock().unwrap_or_else(|e| e.into_inner());
+            format!("{}  {}\n", plugin_id, artifact.display())
+        })
+        .collect()
+}
+
+/// Load the plugin at `artifact`, register its commands and reload it in
+/// the background whenever it changes
+fn start_dev(artifact: &Path, host: &PluginHost) -> Outcome {
+    let Some(runtime) = RUNTIME.as_ref() else {
+        return Outcome::failure(anyhow::anyhow!("plugin runtime unavailable"));
+    };
+    let mut watchers = DEV_WATCHERS.lock().unwrap_or_else(|e| e.into_inner());
+    let artifact = match artifact.canonicalize() {
+        Ok(artifact) => artifact,
+        Err(e) => return Outcome::failure(anyhow::anyhow!("{}: {e}", artifact.display())),
+    };
+    if watchers.contains_key(&artifact) {
+        return Outcome::failure(anyhow::anyhow!(
+            "{} is already being developed",
+            artifact.display()
+        ));
+    }
+    let session = runtime.block_on(async {
+        nxsh_plugin::initialize().await?;
+        DevSession::start(&artifact).await
+    });
+    let session = match session {
+        Ok(session) => session,
+        Err(error) => return Outcome::failure(error),
+    };
+    if let Some(replaced) = session.replaced() {
+        host.unregister(replaced);
+    }
+    host.register(Some(session.plugin_id()));
+    let text = format!(
+        "developing {} from {}\n",
+        session.plugin_id(),
+        artifact.display()
+    );
+
+    let plugin_id = Arc::new(Mutex::new(session.plugin_id().to_string()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let host = host.clone();
+        let plugin_id = Arc::clone(&plugin_id);
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || watch_dev(session, &host, &plugin_id, &stop))
+    };
+    watchers.insert(
+        artifact,
+        DevWatcher {
+            plugin_id,
+            stop,
+            thread,
+        },
+    );
+    Outcome::printed(text)
+}
+
+/// Stop reloading the plugin at `artifact` and unload it
+fn stop_dev(artifact: &Path) -> Outcome {
+    let artifact = artifact
+        .canonicalize()
+        .unwrap_or_else(|_| artifact.to_path_buf());
+    let watcher = DEV_WATCHERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&artifact);
+    let Some(watcher) = watcher else {
+        return Outcome::failure(anyhow::anyhow!(
+            "{} is not being developed",
+            artifact.display()
+        ));
+    };
+    watcher.stop.store(true, Ordering::Relaxed);
+    // The watcher unloads the plugin and unregisters its commands as it ends
+    let _ = watcher.thread.join();
+    let plugin_id = watcher.plugin_id.lock().unwrap_or_else(|e| e.into_inner());
+    Outcome::printed(format!("stopped developing {plugin_id}\n"))
+}
+
+/// Reload `session` whenever its artifact changes, replacing the commands
+/// and hooks of the old plugin in `host` with those of the new one, until
+/// `stop`
+fn watch_dev(
+    mut session: DevSession,
+    host: &PluginHost,
+    plugin_id: &Mutex<String>,
+    stop: &AtomicBool,
+) {
+    let Some(runtime) = RUNTIME.as_ref() else {
+        return;
+    };
+    while !stop.load(Ordering::Relaxed) {
+        if !session.wait_for_change(STOP_CHECK) {
+            continue;
+        }
+        match runtime.block_on(session.reload()) {
+            Ok(reloaded) => {
+                *plugin_id.lock().unwrap_or_else(|e| e.into_inner()) = reloaded.plugin_id.clone();
+                host.unregister(&reloaded.old_id);
+                host.register(Some(&reloaded.plugin_id));
+                eprintln!(
+                    "plugin: reloaded {} ({} -> {})",
+                    reloaded.plugin_id, reloaded.old_version, reloaded.version
+                );
+            }
+            Err(error) => eprintln!(
+                "plugin: reloading {}: {error:#}",
+                session.artifact().display()
+            ),
+        }
+    }
+    let id = session.plugin_id().to_string();
+    if let Err(error) = runtime.block_on(session.stop()) {
+        eprintln!("plugin: unloading {id}: {error:#}");
+    }
+    host.unregister(&id);
+}
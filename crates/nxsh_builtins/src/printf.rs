@@ -0,0 +1,860 @@
+//! `printf` builtin - format and print data
+//!
+//! Syntax:
+//!   printf [-v VAR] FORMAT [ARGUMENT...]
+//!
+//! FORMAT is printed with its backslash escapes interpreted and each
+//! conversion replaced by the next ARGUMENT. While ARGUMENTs are left over
+//! FORMAT is used again; missing ones count as an empty string or zero.
+//! With `-v` the output is assigned to the shell variable VAR instead.
+//!
+//! Conversions take the flags `-`, `+`, space, `#`, `0` and `'` (group
+//! thousands), then a width and a precision, either of which may be `*` to
+//! take it from the next ARGUMENT:
+//!   %d %i              signed decimal integer
+//!   %o %u %x %X        unsigned octal, decimal and hexadecimal integer
+//!   %e %E %f %F %g %G  floating point number
+//!   %c                 first character of the argument
+//!   %s                 the argument as it is
+//!   %b                 the argument with backslash escapes interpreted;
+//!                      `\c` ends all output
+//!   %q                 the argument quoted for reuse as shell input
+//!   %%                 a literal percent sign
+//!
+//! Numeric ARGUMENTs may be decimal, octal with a leading 0, hexadecimal
+//! with a leading 0x, or a quote followed by a character for its code
+//! point. The decimal point and thousands separator follow the locale named
+//! by LC_ALL, LC_NUMERIC or LANG.
+//!
+//! The exit status is 0 on success, 1 when an ARGUMENT is not a valid
+//! number or FORMAT has an invalid conversion, and 2 on usage errors.
+
+use crate::awk::Spec;
+use crate::common::locale_format::numeric_symbols;
+use crate::common::{BuiltinContext, BuiltinResult};
+use crate::read::is_identifier;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The `printf` builtin command implementation
+pub struct PrintfCommand;
+
+impl Builtin for PrintfCommand {
+    fn name(&self) -> &'static str {
+        "printf"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Format and print data"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the ARGUMENTs under the control of FORMAT, reusing FORMAT while arguments \
+         remain, or assign the result to a variable with -v."
+    }
+
+    fn usage(&self) -> &'static str {
+        "printf [-v VAR] FORMAT [ARGUMENT...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Format and print data. Use 'printf \"%-10s %5.1f\\n\" name 3.14159' for aligned \
+         columns."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let numeric = Numeric::new(|name| ctx.get_var(name));
+        let mut outcome = match Invocation::parse(args) {
+            Ok(invocation) => invocation.run(&numeric),
+            Err(message) => Outcome::usage(message),
+        };
+        if let Some(var) = outcome.var.take() {
+            ctx.set_var(var, String::from_utf8_lossy(&outcome.stdout));
+            outcome.stdout.clear();
+        }
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run printf for the legacy dispatcher, writing straight to the process's
+/// standard output and error; `-v` sets an environment variable
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(args)
+}
+
+pub(crate) fn run_legacy(args: &[String]) -> BuiltinResult<i32> {
+    let numeric = Numeric::new(|name| std::env::var(name).ok());
+    let mut outcome = match Invocation::parse(args) {
+        Ok(invocation) => invocation.run(&numeric),
+        Err(message) => Outcome::usage(message),
+    };
+    if let Some(var) = outcome.var.take() {
+        std::env::set_var(var, String::from_utf8_lossy(&outcome.stdout).as_ref());
+        outcome.stdout.clear();
+    }
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed, the variable it goes to instead and the exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    var: Option<String>,
+    status: i32,
+}
+
+impl Outcome {
+    fn usage(message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("printf: {message}\n").into_bytes(),
+            var: None,
+            status: 2,
+        }
+    }
+}
+
+/// The decimal point and thousands separator of the locale in effect
+struct Numeric {
+    decimal: &'static str,
+    separator: &'static str,
+}
+
+impl Numeric {
+    /// The numeric conventions of the locale the variables named by
+    /// `LC_ALL`, `LC_NUMERIC` and `LANG` select, looked up through VAR
+    fn new(var: impl Fn(&str) -> Option<String>) -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty()))
+            .unwrap_or_default();
+        let (decimal, separator) = numeric_symbols(&locale);
+        Numeric { decimal, separator }
+    }
+}
+
+/// A parsed command line
+struct Invocation<'a> {
+    var: Option<&'a str>,
+    format: &'a str,
+    args: &'a [String],
+}
+
+impl<'a> Invocation<'a> {
+    fn parse(args: &'a [String]) -> Result<Self, String> {
+        let mut var = None;
+        let mut rest = args;
+        while let Some(arg) = rest.first() {
+            match arg.as_str() {
+                "--" => {
+                    rest = &rest[1..];
+                    break;
+                }
+                "-v" => {
+                    let name = rest
+                        .get(1)
+                        .ok_or_else(|| "-v: option requires an argument".to_string())?;
+                    var = Some(name.as_str());
+                    rest = &rest[2..];
+                }
+                other => {
+                    if let Some(name) = other.strip_prefix("-v") {
+                        var = Some(name);
+                        rest = &rest[1..];
+                    } else if other.len() > 1 && other.starts_with('-') {
+                        return Err(format!("{other}: invalid option"));
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(name) = var.filter(|name| !is_identifier(name)) {
+            return Err(format!("`{name}': not a valid identifier"));
+        }
+        let Some((format, args)) = rest.split_first() else {
+            return Err("usage: printf [-v var] format [arguments]".to_string());
+        };
+        Ok(Invocation {
+            var,
+            format: format.as_str(),
+            args,
+        })
+    }
+
+    fn run(&self, numeric: &Numeric) -> Outcome {
+        let mut printer = Printer {
+            args: self.args,
+            next: 0,
+            numeric,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+        };
+        // The format is reused while it consumes arguments and some are left
+        loop {
+            let start = printer.next;
+            if !printer.pass(self.format) || printer.next == start {
+                break;
+            }
+            if printer.next >= self.args.len() {
+                break;
+            }
+        }
+        Outcome {
+            stdout: printer.stdout,
+            stderr: printer.stderr,
+            var: self.var.map(str::to_string),
+            status: printer.status,
+        }
+    }
+}
+
+/// Output and argument position while a format is applied
+struct Printer<'a> {
+    args: &'a [String],
+    next: usize,
+    numeric: &'a Numeric,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl<'a> Printer<'a> {
+    /// Apply FORMAT once; false when output has to stop, after `\c` or an
+    /// invalid conversion
+    fn pass(&mut self, format: &str) -> bool {
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if !escape(&mut chars, &mut self.stdout, false) {
+                        return false;
+                    }
+                }
+                '%' => {
+                    if !self.conversion(&mut chars) {
+                        return false;
+                    }
+                }
+                other => {
+                    let mut buffer = [0; 4];
+                    self.stdout
+                        .extend(other.encode_utf8(&mut buffer).as_bytes());
+                }
+            }
+        }
+        true
+    }
+
+    /// Print the conversion after a `%`; false when output has to stop
+    fn conversion(&mut self, chars: &mut Peekable<Chars>) -> bool {
+        let mut spec = Spec::default();
+        let mut group = false;
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                '\'' => group = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        if let Some(width) = self.number(chars) {
+            if width < 0 {
+                spec.left = true;
+            }
+            spec.width = width.unsigned_abs() as usize;
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            // A negative precision from `*` counts as none at all
+            spec.precision = match self.number(chars) {
+                Some(precision) if precision < 0 => None,
+                precision => Some(precision.unwrap_or(0) as usize),
+            };
+        }
+        // Length modifiers mean nothing here
+        while chars
+            .peek()
+            .is_some_and(|c| matches!(c, 'h' | 'l' | 'L' | 'q' | 'j' | 'z' | 't'))
+        {
+            chars.next();
+        }
+        let Some(conversion) = chars.next() else {
+            return self.invalid("`%': missing format character");
+        };
+        let body = match conversion {
+            '%' => {
+                self.stdout.push(b'%');
+                return true;
+            }
+            's' => {
+                let text = self.string();
+                let text = match spec.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text.to_string(),
+                };
+                spec.pad(text, false)
+            }
+            'c' => spec.pad(self.string().chars().take(1).collect(), false),
+            'q' => spec.pad(quote(self.string()), false),
+            'b' => {
+                let mut expanded = Vec::new();
+                let mut arg = self.string().chars().peekable();
+                let mut go_on = true;
+                while let Some(c) = arg.next() {
+                    if c != '\\' {
+                        let mut buffer = [0; 4];
+                        expanded.extend(c.encode_utf8(&mut buffer).as_bytes());
+                    } else if !escape(&mut arg, &mut expanded, true) {
+                        go_on = false;
+                        break;
+                    }
+                }
+                if let Some(precision) = spec.precision {
+                    expanded.truncate(precision);
+                }
+                self.stdout.extend(pad_bytes(&spec, expanded));
+                return go_on;
+            }
+            'd' | 'i' => {
+                let n = self.integer();
+                let magnitude = n.clamp(i64::MIN.into(), i64::MAX.into()).unsigned_abs();
+                let body = spec.whole(n < 0, magnitude as u64, 10, false);
+                spec.pad(self.localize(body, group), true)
+            }
+            'o' | 'u' | 'x' | 'X' => {
+                let n = self.integer();
+                // Negative numbers wrap around as in C
+                let magnitude = if n < 0 {
+                    n.max(i64::MIN.into()) as i64 as u64
+                } else {
+                    n.min(u64::MAX.into()) as u64
+                };
+                let (radix, upper) = match conversion {
+                    'o' => (8, false),
+                    'u' => (10, false),
+                    'x' => (16, false),
+                    _ => (16, true),
+                };
+                let body = spec.whole(false, magnitude, radix, upper);
+                spec.pad(self.localize(body, group && radix == 10), true)
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let n = self.float();
+                let group = group && !matches!(conversion, 'e' | 'E');
+                let body = spec.float(n, conversion);
+                spec.pad(self.localize(body, group), true)
+            }
+            other => return self.invalid(&format!("`{other}': invalid format character")),
+        };
+        self.stdout.extend(body.into_bytes());
+        true
+    }
+
+    /// A width or precision: digits, or `*` for the next argument
+    fn number(&mut self, chars: &mut Peekable<Chars>) -> Option<i64> {
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            let n = self.integer();
+            return Some(n.clamp(i32::MIN.into(), i32::MAX.into()) as i64);
+        }
+        let mut digits = String::new();
+        while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*d);
+            chars.next();
+        }
+        // Widths past what fits in memory are not worth honouring
+        digits.parse::<i64>().ok().map(|n| n.min(i32::MAX.into()))
+    }
+
+    fn argument(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg.as_str())
+    }
+
+    fn string(&mut self) -> &'a str {
+        self.argument().unwrap_or_default()
+    }
+
+    fn integer(&mut self) -> i128 {
+        let Some(text) = self.argument() else {
+            return 0;
+        };
+        let (n, problem) = parse_integer(text);
+        self.complain(text, problem);
+        n
+    }
+
+    fn float(&mut self) -> f64 {
+        let Some(text) = self.argument() else {
+            return 0.0;
+        };
+        let (n, problem) = parse_float(text, self.numeric.decimal);
+        self.complain(text, problem);
+        n
+    }
+
+    fn complain(&mut self, text: &str, problem: Option<Problem>) {
+        match problem {
+            Some(Problem::Invalid) => {
+                self.stderr
+                    .extend(format!("printf: {text}: invalid number\n").into_bytes());
+                self.status = 1;
+            }
+            Some(Problem::Range) => self.stderr.extend(
+                format!("printf: warning: {text}: Numerical result out of range\n").into_bytes(),
+            ),
+            None => {}
+        }
+    }
+
+    fn invalid(&mut self, message: &str) -> bool {
+        self.stderr
+            .extend(format!("printf: {message}\n").into_bytes());
+        self.status = 1;
+        false
+    }
+
+    /// A formatted number with the locale's decimal point and, when GROUP is
+    /// set, its thousands separator in the integer part
+    fn localize(&self, body: String, group: bool) -> String {
+        let Some((head, tail)) = body.split_once('\0') else {
+            return body;
+        };
+        let end = tail
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (whole, rest) = tail.split_at(end);
+        let mut grouped = String::new();
+        for (index, digit) in whole.chars().enumerate() {
+            if group && index > 0 && (whole.len() - index) % 3 == 0 {
+                grouped.push_str(self.numeric.separator);
+            }
+            grouped.push(digit);
+        }
+        let rest = match rest.strip_prefix('.') {
+            Some(fraction) => format!("{}{fraction}", self.numeric.decimal),
+            None => rest.to_string(),
+        };
+        format!("{head}\0{grouped}{rest}")
+    }
+}
+
+/// What was wrong with a numeric argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Problem {
+    /// It is not (all) a number; the leading number, if any, is used
+    Invalid,
+    /// It is too large, and the nearest value that fits is used
+    Range,
+}
+
+/// An integer argument as C's strtoimax reads it, or the code point of
+/// the character after a leading quote
+fn parse_integer(text: &str) -> (i128, Option<Problem>) {
+    let trimmed = text.trim_start();
+    if let Some(quoted) = trimmed.strip_prefix(['\'', '"']) {
+        return (quoted.chars().next().map_or(0, |c| c as i128), None);
+    }
+    if trimmed.is_empty() {
+        return (
+            0,
+            if text.is_empty() {
+                None
+            } else {
+                Some(Problem::Invalid)
+            },
+        );
+    }
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (radix, digits) = if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        (16, hex)
+    } else if let Some(octal) = unsigned.strip_prefix('0').filter(|rest| !rest.is_empty()) {
+        (8, octal)
+    } else {
+        (10, unsigned)
+    };
+    let limit = u64::MAX as i128;
+    let mut n: i128 = 0;
+    let mut used = 0;
+    let mut range = false;
+    for c in digits.chars() {
+        let Some(digit) = c.to_digit(radix) else {
+            break;
+        };
+        n = n * radix as i128 + digit as i128;
+        if n > limit {
+            n = limit;
+            range = true;
+        }
+        used += 1;
+    }
+    // "0x" alone reads as the 0 before the x
+    let complete = used == digits.chars().count() && (used > 0 || radix == 8);
+    let n = if negative { -n } else { n };
+    let problem = if !complete {
+        Some(Problem::Invalid)
+    } else if range {
+        Some(Problem::Range)
+    } else {
+        None
+    };
+    (n, problem)
+}
+
+/// A floating point argument, with either `.` or the locale's DECIMAL
+/// point; integers in hexadecimal or octal and quoted characters are read
+/// as for the integer conversions
+fn parse_float(text: &str, decimal: &str) -> (f64, Option<Problem>) {
+    let trimmed = text.trim_start();
+    let unsigned = trimmed.trim_start_matches(['+', '-']);
+    if trimmed.starts_with(['\'', '"']) || unsigned.starts_with("0x") || unsigned.starts_with("0X")
+    {
+        let (n, problem) = parse_integer(text);
+        return (n as f64, problem);
+    }
+    if trimmed.is_empty() {
+        return (
+            0.0,
+            if text.is_empty() {
+                None
+            } else {
+                Some(Problem::Invalid)
+            },
+        );
+    }
+    let text = trimmed.replacen(decimal, ".", 1);
+    // The longest leading part that is a number
+    let end = (1..=text.len())
+        .rev()
+        .filter(|&end| text.is_char_boundary(end))
+        .find(|&end| text[..end].parse::<f64>().is_ok());
+    match end {
+        Some(end) => {
+            let n: f64 = text[..end].parse().unwrap_or(0.0);
+            let problem = if end < text.len() {
+                Some(Problem::Invalid)
+            } else if n.is_infinite() && !text.to_ascii_lowercase().contains("inf") {
+                Some(Problem::Range)
+            } else {
+                None
+            };
+            (n, problem)
+        }
+        None => (0.0, Some(Problem::Invalid)),
+    }
+}
+
+/// Interpret the escape after a backslash, writing its bytes to OUT; false
+/// on `\c`. Octal escapes are `\NNN` in formats and `\0NNN` in `%b`
+/// arguments, which also keep `\"`, `\'` and `\?` as they are.
+fn escape(chars: &mut Peekable<Chars>, out: &mut Vec<u8>, argument: bool) -> bool {
+    let Some(c) = chars.next() else {
+        out.push(b'\\');
+        return true;
+    };
+    let byte = match c {
+        'a' => 0x07,
+        'b' => 0x08,
+        'e' | 'E' => 0x1b,
+        'f' => 0x0c,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 0x0b,
+        '\\' => b'\\',
+        '"' | '\'' | '?' if !argument => c as u8,
+        'c' => return false,
+        '0'..='7' => {
+            let extra = if c == '0' && argument { 3 } else { 2 };
+            let mut value = c.to_digit(8).unwrap_or(0);
+            for _ in 0..extra {
+                match chars.peek().and_then(|d| d.to_digit(8)) {
+                    Some(digit) => {
+                        value = value * 8 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            value as u8
+        }
+        'x' | 'u' | 'U' => {
+            let most = match c {
+                'x' => 2,
+                'u' => 4,
+                _ => 8,
+            };
+            let mut value: u32 = 0;
+            let mut used = 0;
+            while used < most {
+                match chars.peek().and_then(|d| d.to_digit(16)) {
+                    Some(digit) => {
+                        value = value * 16 + digit;
+                        chars.next();
+                        used += 1;
+                    }
+                    None => break,
+                }
+            }
+            if used == 0 {
+                out.push(b'\\');
+                out.push(c as u8);
+                return true;
+            }
+            if c == 'x' {
+                value as u8
+            } else {
+                let mut buffer = [0; 4];
+                let text = char::from_u32(value)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    .encode_utf8(&mut buffer);
+                out.extend(text.as_bytes());
+                return true;
+            }
+        }
+        other => {
+            let mut buffer = [0; 4];
+            out.push(b'\\');
+            out.extend(other.encode_utf8(&mut buffer).as_bytes());
+            return true;
+        }
+    };
+    out.push(byte);
+    true
+}
+
+/// `%b` output padded to the width, which counts bytes when it is not
+/// UTF-8
+fn pad_bytes(spec: &Spec, bytes: Vec<u8>) -> Vec<u8> {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return spec.pad(text, false).into_bytes(),
+        Err(e) => e.into_bytes(),
+    };
+    let fill = vec![b' '; spec.width.saturating_sub(bytes.len())];
+    if spec.left {
+        [bytes, fill].concat()
+    } else {
+        [fill, bytes].concat()
+    }
+}
+
+/// TEXT quoted so the shell reads it back as one word: backslashes before
+/// special characters, or `$'...'` when it has control characters
+fn quote(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    if text.chars().any(char::is_control) {
+        let mut quoted = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\x07' => quoted.push_str("\\a"),
+                '\x08' => quoted.push_str("\\b"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\x0c' => quoted.push_str("\\f"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '\x0b' => quoted.push_str("\\v"),
+                '\\' | '\'' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if c.is_control() => {
+                    let mut buffer = [0; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        quoted.push_str(&format!("\\{byte:03o}"));
+                    }
+                }
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+    let mut quoted = String::new();
+    for (index, c) in text.chars().enumerate() {
+        let special = matches!(
+            c,
+            ' ' | '\''
+                | '"'
+                | '\\'
+                | '|'
+                | '&'
+                | ';'
+                | '('
+                | ')'
+                | '<'
+                | '>'
+                | '!'
+                | '{'
+                | '}'
+                | '*'
+                | '['
+                | ']'
+                | '?'
+                | '^'
+                | '$'
+                | '`'
+                | ','
+        ) || (index == 0 && matches!(c, '#' | '~'));
+        if special {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(list: &[&str]) -> (String, String, i32) {
+        printf_in("C", list)
+    }
+
+    fn printf_in(locale: &str, list: &[&str]) -> (String, String, i32) {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        let numeric = Numeric::new(|name| (name == "LANG").then(|| locale.to_string()));
+        let outcome = match Invocation::parse(&args) {
+            Ok(invocation) => invocation.run(&numeric),
+            Err(message) => Outcome::usage(message),
+        };
+        (
+            String::from_utf8_lossy(&outcome.stdout).into_owned(),
+            String::from_utf8_lossy(&outcome.stderr).into_owned(),
+            outcome.status,
+        )
+    }
+
+    fn out(list: &[&str]) -> String {
+        printf(list).0
+    }
+
+    #[test]
+    fn formats_integers() {
+        assert_eq!(
+            out(&["%d|%5d|%-5d|%05d", "42", "42", "42", "-42"]),
+            "42|   42|42   |-0042"
+        );
+        assert_eq!(out(&["%+d % d %.3d", "7", "7", "7"]), "+7  7 007");
+        assert_eq!(
+            out(&["%o %#o %x %#X %u", "8", "8", "255", "255", "-1"]),
+            "10 010 ff 0XFF 18446744073709551615"
+        );
+        assert_eq!(out(&["%d %d %d", "0x1f", "017", "'A"]), "31 15 65");
+        assert_eq!(out(&["%i", "9223372036854775807"]), "9223372036854775807");
+        assert_eq!(out(&["%*d|%-*d|", "4", "1", "3", "2"]), "   1|2  |");
+    }
+
+    #[test]
+    fn formats_floats() {
+        assert_eq!(
+            out(&["%f %.2f %8.3f", "3.14159", "2.5", "-1"]),
+            "3.141590 2.50   -1.000"
+        );
+        assert_eq!(
+            out(&["%e %E", "12345.678", "0.000123"]),
+            "1.234568e+04 1.230000E-04"
+        );
+        assert_eq!(
+            out(&["%g %g %g %G", "100000", "1000000", "0.0001", "1e-5"]),
+            "100000 1e+06 0.0001 1E-05"
+        );
+        assert_eq!(out(&["%.0f %F", "2.5", "inf"]), "2 INF");
+    }
+
+    #[test]
+    fn formats_strings() {
+        assert_eq!(
+            out(&["[%s] [%5s] [%-5s] [%.2s]", "a", "b", "c", "xyz"]),
+            "[a] [    b] [c    ] [xy]"
+        );
+        assert_eq!(out(&["%c%c", "hello", ""]), "h");
+        assert_eq!(out(&["%b", "a\\tb\\0101\\n"]), "a\tbA\n");
+        assert_eq!(out(&["%b|%s", "x\\cy", "never"]), "x");
+        assert_eq!(out(&["%q %q %q", "a b", "", "it's"]), "a\\ b '' it\\'s");
+        assert_eq!(out(&["%q", "a\nb"]), "$'a\\nb'");
+        assert_eq!(out(&["100%%\\n"]), "100%\n");
+    }
+
+    #[test]
+    fn interprets_format_escapes() {
+        assert_eq!(out(&["\\101\\x42\\u00e9\\\"\\q"]), "AB\u{e9}\"\\q");
+        assert_eq!(out(&["a\\cb"]), "a");
+    }
+
+    #[test]
+    fn reuses_the_format_for_extra_arguments() {
+        assert_eq!(out(&["%s=%d\\n", "a", "1", "b"]), "a=1\nb=0\n");
+        assert_eq!(out(&["x\\n", "ignored"]), "x\n");
+        assert_eq!(out(&["%s\\n"]), "\n");
+    }
+
+    #[test]
+    fn reports_bad_numbers_and_formats() {
+        assert_eq!(
+            printf(&["%d|", "12abc", "5"]),
+            (
+                "12|5|".to_string(),
+                "printf: 12abc: invalid number\n".to_string(),
+                1
+            )
+        );
+        let (stdout, stderr, status) = printf(&["%d", "99999999999999999999"]);
+        assert_eq!(stdout, "9223372036854775807");
+        assert!(stderr.contains("Numerical result out of range"));
+        assert_eq!(status, 0);
+        assert_eq!(
+            printf(&["a%kb"]),
+            (
+                "a".to_string(),
+                "printf: `k': invalid format character\n".to_string(),
+                1
+            )
+        );
+        assert_eq!(printf(&[]).2, 2);
+        assert_eq!(printf(&["-x"]).1, "printf: -x: invalid option\n");
+        assert_eq!(
+            printf(&["-v", "1a", "x"]).1,
+            "printf: `1a': not a valid identifier\n"
+        );
+    }
+
+    #[test]
+    fn follows_the_numeric_locale() {
+        assert_eq!(
+            printf_in("C", &["%'d %'.1f", "1234567", "1234.5"]).0,
+            "1234567 1234.5"
+        );
+        assert_eq!(
+            printf_in("en_US.UTF-8", &["%'d %'.1f", "1234567", "1234.5"]).0,
+            "1,234,567 1,234.5"
+        );
+        assert_eq!(
+            printf_in("de_DE.UTF-8", &["%'d %.2f", "1234567", "3,5"]).0,
+            "1.234.567 3,50"
+        );
+    }
+}
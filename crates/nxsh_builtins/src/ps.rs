@@ -1,404 +1,645 @@
-use crate::common::{BuiltinContext, BuiltinResult};
+//! `ps` builtin - report a snapshot of the running processes
+//!
+//! Syntax:
+//!   ps [-eAf] [-o FIELD[=HEADER],...] [-p PID,...] [-u USER,...] [-C NAME,...]
+//!      [--ppid PID,...] [--sort [+|-]KEY,...] [--no-headers]
+//!   ps [aux]
+//!
+//! Without a selection the processes of the current user on the shell's
+//! terminal are listed, or all of the user's processes when the shell has
+//! no terminal. `-e` and `-A` list every process; `-p`, `-u`, `-C` and
+//! `--ppid` list those with one of the given process IDs, owners, names or
+//! parents, and when several are given a process matching any of them is
+//! listed. The BSD letters `a`, `x` and `u` drop the user and terminal
+//! conditions and pick the user oriented format, so `ps aux` lists every
+//! process in detail.
+//!
+//! The default columns are PID, TTY, TIME and CMD. `-f` adds the owner,
+//! parent, CPU use and start time and shows whole command lines. `-o`
+//! picks the columns from pid, ppid, user, stat (or s), %cpu, c, %mem,
+//! rss, vsz (both in KiB), tty, time, start (or stime), ni, nlwp, comm and
+//! args (or cmd), each optionally renamed with `=HEADER`.
+//!
+//! `--sort` orders the list by the given fields, in descending order for a
+//! field prefixed with `-`; the default order is by process ID.
+//!
+//! The exit status is 0 on success and 1 if a selection matched no process
+//! or the processes could not be read.
 
-/// Display information about running processes
-pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    let mut show_all = false;
-    let mut show_full = false;
-    let mut show_threads = false;
-    let mut show_user_format = false;
-    let mut pid_filter: Option<u32> = None;
-
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-a" | "--all" => show_all = true,
-            "-f" | "--full" => show_full = true,
-            "-T" | "--threads" => show_threads = true,
-            "-u" | "--user" => show_user_format = true,
-            "-p" | "--pid" => {
-                if i + 1 >= args.len() {
-                    eprintln!("ps: option '{}' requires an argument", args[i]);
-                    return Ok(1);
-                }
-                i += 1;
-                match args[i].parse::<u32>() {
-                    Ok(pid) => pid_filter = Some(pid),
-                    Err(_) => {
-                        eprintln!("ps: invalid PID '{}'", args[i]);
-                        return Ok(1);
-                    }
-                }
-            }
-            "-h" | "--help" => {
-                print_help();
-                return Ok(0);
-            }
-            "aux" => {
-                // BSD-style format
-                show_all = true;
-                show_user_format = true;
-            }
-            arg if arg.starts_with('-') => {
-                eprintln!("ps: invalid option '{arg}'");
-                return Ok(1);
-            }
-            _ => {
-                eprintln!("ps: unexpected argument '{}'", args[i]);
-                return Ok(1);
-            }
-        }
-        i += 1;
+use crate::common::{BuiltinContext, BuiltinResult};
+use chrono::{DateTime, Datelike, Local};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::{ProcessEntry, ProcessSnapshot};
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+
+/// The `ps` builtin command implementation
+pub struct PsCommand;
+
+impl Builtin for PsCommand {
+    fn name(&self) -> &'static str {
+        "ps"
     }
 
-    match get_process_info(
-        show_all,
-        show_full,
-        show_threads,
-        show_user_format,
-        pid_filter,
-    ) {
-        Ok(processes) => {
-            display_processes(&processes, show_full, show_user_format);
-            Ok(0)
-        }
-        Err(e) => {
-            eprintln!("ps: {e}");
-            Ok(1)
-        }
+    fn synopsis(&self) -> &'static str {
+        "Report a snapshot of the running processes"
     }
-}
 
-#[derive(Debug, Clone)]
-struct ProcessInfo {
-    pid: u32,
-    ppid: u32,
-    user: String,
-    command: String,
-    cpu_percent: f32,
-    mem_percent: f32,
-    virtual_size: u64,
-    resident_size: u64,
-    state: String,
-    start_time: String,
-    tty: String,
-    priority: i32,
-    nice: i32,
-}
-
-fn get_process_info(
-    show_all: bool,
-    _show_full: bool,
-    _show_threads: bool,
-    _show_user_format: bool,
-    pid_filter: Option<u32>,
-) -> Result<Vec<ProcessInfo>, Box<dyn std::error::Error>> {
-    let processes;
-
-    #[cfg(target_os = "linux")]
-    {
-        processes = get_linux_processes(show_all, pid_filter)?;
+    fn description(&self) -> &'static str {
+        "List processes selected by ID, owner, name or parent, with columns chosen \
+         by -o and sorted by any of them."
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        processes = get_windows_processes(show_all, pid_filter)?;
+    fn usage(&self) -> &'static str {
+        "ps [-eAf] [-o FIELD[=HEADER],...] [-p PID,...] [-u USER,...] [-C NAME,...] \
+         [--ppid PID,...] [--sort [+|-]KEY,...] [--no-headers]"
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        processes = get_macos_processes(show_all, pid_filter)?;
+    fn help(&self) -> &'static str {
+        "Report a snapshot of the running processes. Use 'ps aux' for every process \
+         or 'ps -e -o pid,user,%mem,comm --sort -%mem' for the largest ones."
     }
 
-    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
-    {
-        // Fallback for other systems
-        processes = get_fallback_processes(show_all, pid_filter)?;
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
+}
 
-    Ok(processes)
+/// Run ps for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
 }
 
-#[cfg(target_os = "linux")]
-fn get_linux_processes(
-    show_all: bool,
-    pid_filter: Option<u32>,
-) -> Result<Vec<ProcessInfo>, Box<dyn std::error::Error>> {
-    let mut processes = Vec::new();
-    let proc_dir = Path::new("/proc");
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
 
-    if !proc_dir.exists() {
-        return Err("Cannot access /proc filesystem".into());
+impl Outcome {
+    fn failure(message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("ps: {message}\n").into_bytes(),
+            status: 1,
+        }
     }
+}
 
-    for entry in fs::read_dir(proc_dir)? {
-        let entry = entry?;
-        let name = entry.file_name();
-        let name_str = name.to_string_lossy();
-
-        if let Ok(pid) = name_str.parse::<u32>() {
-            if let Some(filter_pid) = pid_filter {
-                if pid != filter_pid {
-                    continue;
-                }
-            }
+fn run(args: &[String]) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return Outcome::failure(message),
+    };
+    let snapshot = match ProcessSnapshot::capture() {
+        Ok(snapshot) => snapshot,
+        Err(e) => return Outcome::failure(e.to_string()),
+    };
 
-            if let Ok(process) = parse_linux_process(pid) {
-                if show_all || process.tty != "?" {
-                    processes.push(process);
-                }
+    let me = snapshot.get(std::process::id());
+    let me = Me {
+        user: me.map_or_else(
+            || std::env::var("USER").unwrap_or_default(),
+            |me| me.user.clone(),
+        ),
+        tty: me.and_then(|me| me.tty.clone()),
+    };
+    let mut processes: Vec<&ProcessEntry> = snapshot
+        .processes()
+        .iter()
+        .filter(|process| options.selects(process, &me))
+        .collect();
+    for &(field, descending) in options.sort.iter().rev() {
+        processes.sort_by(|a, b| {
+            let order = field.compare(a, b);
+            if descending {
+                order.reverse()
+            } else {
+                order
             }
-        }
+        });
     }
 
-    Ok(processes)
+    let now = Local::now();
+    let columns = options.columns();
+    let rows: Vec<Vec<String>> = processes
+        .iter()
+        .map(|process| {
+            columns
+                .iter()
+                .map(|column| column.field.value(process, &snapshot, &now))
+                .collect()
+        })
+        .collect();
+    Outcome {
+        stdout: render(&columns, &rows, options.headers).into_bytes(),
+        stderr: Vec::new(),
+        status: i32::from(rows.is_empty() && options.selected()),
+    }
 }
 
-#[cfg(target_os = "linux")]
-fn parse_linux_process(pid: u32) -> Result<ProcessInfo, Box<dyn std::error::Error>> {
-    let stat_path = format!("/proc/{}/stat", pid);
-    let cmdline_path = format!("/proc/{}/cmdline", pid);
-    let status_path = format!("/proc/{}/status", pid);
+/// The user running the shell and its terminal, which pick the processes
+/// listed when no selection is given
+struct Me {
+    user: String,
+    tty: Option<String>,
+}
 
-    let stat_content = fs::read_to_string(&stat_path)?;
-    let stat_fields: Vec<&str> = stat_content.split_whitespace().collect();
+/// A column of the listing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Pid,
+    Ppid,
+    User,
+    State,
+    Cpu,
+    /// `c`, the whole part of the CPU percentage
+    Percent,
+    Mem,
+    Rss,
+    Vsz,
+    Tty,
+    Time,
+    Start,
+    Nice,
+    Threads,
+    Comm,
+    Args,
+}
 
-    if stat_fields.len() < 24 {
-        return Err("Invalid stat file format".into());
+/// The names `-o` and `--sort` take, with the header each shows by default
+const FIELDS: &[(&str, Field, &str)] = &[
+    ("pid", Field::Pid, "PID"),
+    ("ppid", Field::Ppid, "PPID"),
+    ("user", Field::User, "USER"),
+    ("stat", Field::State, "STAT"),
+    ("s", Field::State, "S"),
+    ("state", Field::State, "S"),
+    ("%cpu", Field::Cpu, "%CPU"),
+    ("pcpu", Field::Cpu, "%CPU"),
+    ("c", Field::Percent, "C"),
+    ("%mem", Field::Mem, "%MEM"),
+    ("pmem", Field::Mem, "%MEM"),
+    ("rss", Field::Rss, "RSS"),
+    ("vsz", Field::Vsz, "VSZ"),
+    ("tty", Field::Tty, "TT"),
+    ("time", Field::Time, "TIME"),
+    ("start", Field::Start, "START"),
+    ("stime", Field::Start, "STIME"),
+    ("ni", Field::Nice, "NI"),
+    ("nice", Field::Nice, "NI"),
+    ("nlwp", Field::Threads, "NLWP"),
+    ("comm", Field::Comm, "COMMAND"),
+    ("args", Field::Args, "COMMAND"),
+    ("cmd", Field::Args, "CMD"),
+    ("command", Field::Args, "COMMAND"),
+];
+
+impl Field {
+    /// The field named `name` and its default header
+    fn lookup(name: &str) -> Option<(Field, &'static str)> {
+        FIELDS
+            .iter()
+            .find(|(known, ..)| known.eq_ignore_ascii_case(name))
+            .map(|&(_, field, header)| (field, header))
     }
 
-    let ppid = stat_fields[3].parse::<u32>().unwrap_or(0);
-    let state = stat_fields[2].to_string();
-    let priority = stat_fields[17].parse::<i32>().unwrap_or(0);
-    let nice = stat_fields[18].parse::<i32>().unwrap_or(0);
-    let virtual_size = stat_fields[22].parse::<u64>().unwrap_or(0);
-    let resident_size = stat_fields[23].parse::<u64>().unwrap_or(0) * 4096; // Convert pages to bytes
-
-    let cmdline = fs::read_to_string(&cmdline_path)
-        .unwrap_or_default()
-        .replace('\0', " ")
-        .trim()
-        .to_string();
-
-    let command = if cmdline.is_empty() {
-        format!(
-            "[{}]",
-            stat_fields
-                .get(1)
-                .unwrap_or(&"unknown")
-                .trim_matches(['(', ')'])
+    /// Whether the column holds numbers, which are aligned right
+    fn numeric(self) -> bool {
+        matches!(
+            self,
+            Field::Pid
+                | Field::Ppid
+                | Field::Cpu
+                | Field::Percent
+                | Field::Mem
+                | Field::Rss
+                | Field::Vsz
+                | Field::Time
+                | Field::Nice
+                | Field::Threads
         )
-    } else {
-        cmdline
-    };
+    }
 
-    // Try to get user info from status file
-    let user = if let Ok(status_content) = fs::read_to_string(&status_path) {
-        parse_user_from_status(&status_content).unwrap_or_else(|| "unknown".to_string())
-    } else {
-        "unknown".to_string()
-    };
+    fn value(
+        self,
+        process: &ProcessEntry,
+        snapshot: &ProcessSnapshot,
+        now: &DateTime<Local>,
+    ) -> String {
+        match self {
+            Field::Pid => process.pid.to_string(),
+            Field::Ppid => process.ppid.to_string(),
+            Field::User => process.user.clone(),
+            Field::State => process.state.code().to_string(),
+            Field::Cpu => format!("{:.1}", process.cpu_percent),
+            Field::Percent => (process.cpu_percent as u64).to_string(),
+            Field::Mem => format!("{:.1}", snapshot.memory_percent(process)),
+            Field::Rss => (process.rss / 1024).to_string(),
+            Field::Vsz => (process.vsz / 1024).to_string(),
+            Field::Tty => process.tty.clone().unwrap_or_else(|| "?".to_string()),
+            Field::Time => cpu_time(process.cpu_time),
+            Field::Start => process
+                .start_time
+                .map_or_else(|| "?".to_string(), |start| start_time(start, now)),
+            Field::Nice => process.nice.to_string(),
+            Field::Threads => process.threads.to_string(),
+            Field::Comm => process.name.clone(),
+            Field::Args => process.command_line.clone(),
+        }
+    }
 
-    Ok(ProcessInfo {
-        pid,
-        ppid,
-        user,
-        command,
-        cpu_percent: 0.0, // Would need sampling over time
-        mem_percent: 0.0, // Would need system memory info
-        virtual_size,
-        resident_size,
-        state,
-        start_time: "?".to_string(), // Would need boot time calculation
-        tty: "?".to_string(),        // Would need tty parsing
-        priority,
-        nice,
-    })
+    /// `%mem` orders by resident memory, its percentage of the same total
+    fn compare(self, a: &ProcessEntry, b: &ProcessEntry) -> Ordering {
+        match self {
+            Field::Pid => a.pid.cmp(&b.pid),
+            Field::Ppid => a.ppid.cmp(&b.ppid),
+            Field::User => a.user.cmp(&b.user),
+            Field::State => a.state.code().cmp(&b.state.code()),
+            Field::Cpu | Field::Percent => a.cpu_percent.total_cmp(&b.cpu_percent),
+            Field::Mem | Field::Rss => a.rss.cmp(&b.rss),
+            Field::Vsz => a.vsz.cmp(&b.vsz),
+            Field::Tty => a.tty.cmp(&b.tty),
+            Field::Time => a.cpu_time.cmp(&b.cpu_time),
+            Field::Start => a.start_time.cmp(&b.start_time),
+            Field::Nice => a.nice.cmp(&b.nice),
+            Field::Threads => a.threads.cmp(&b.threads),
+            Field::Comm => a.name.cmp(&b.name),
+            Field::Args => a.command_line.cmp(&b.command_line),
+        }
+    }
 }
 
-#[cfg(target_os = "linux")]
-fn parse_user_from_status(content: &str) -> Option<String> {
-    for line in content.lines() {
-        if line.starts_with("Uid:") {
-            if let Some(uid_str) = line.split_whitespace().nth(1) {
-                if let Ok(uid) = uid_str.parse::<u32>() {
-                    // In a real implementation, would look up username from /etc/passwd
-                    return Some(format!("uid{}", uid));
-                }
-            }
-        }
+/// CPU time as `[DD-]HH:MM:SS`
+fn cpu_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    let clock = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    );
+    match secs / 86_400 {
+        0 => clock,
+        days => format!("{days}-{clock}"),
     }
-    None
 }
 
-#[cfg(target_os = "windows")]
-fn get_windows_processes(
-    _show_all: bool,
-    pid_filter: Option<u32>,
-) -> Result<Vec<ProcessInfo>, Box<dyn std::error::Error>> {
-    let mut processes = Vec::new();
+/// The start time as the hour for processes started today, the day for
+/// those started this year and the year otherwise
+fn start_time(start: SystemTime, now: &DateTime<Local>) -> String {
+    let start: DateTime<Local> = start.into();
+    let format = if start.date_naive() == now.date_naive() {
+        "%H:%M"
+    } else if start.year() == now.year() {
+        "%b%d"
+    } else {
+        "%Y"
+    };
+    start.format(format).to_string()
+}
 
-    // Simplified Windows implementation
-    // In a real implementation, would use Windows APIs like EnumProcesses
-    let current_pid = std::process::id();
+#[derive(Debug, Clone, PartialEq)]
+struct Column {
+    field: Field,
+    header: String,
+}
 
-    if let Some(filter_pid) = pid_filter {
-        if filter_pid == current_pid {
-            processes.push(create_current_process_info());
+impl Column {
+    fn new(field: Field, header: &str) -> Self {
+        Column {
+            field,
+            header: header.to_string(),
         }
-    } else {
-        processes.push(create_current_process_info());
     }
+}
 
-    Ok(processes)
+/// The column sets of `ps`, `ps -f` and `ps u`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Default,
+    Full,
+    User,
 }
 
-#[cfg(any(
-    target_os = "macos",
-    not(any(target_os = "linux", target_os = "windows"))
-))]
-fn get_macos_processes(
-    _show_all: bool,
-    pid_filter: Option<u32>,
-) -> Result<Vec<ProcessInfo>, Box<dyn std::error::Error>> {
-    get_fallback_processes(_show_all, pid_filter)
+/// Parsed command line
+#[derive(Debug)]
+struct Options {
+    all: bool,
+    format: Format,
+    /// `-o`, replacing the format's columns
+    columns: Vec<Column>,
+    pids: Vec<u32>,
+    users: Vec<String>,
+    names: Vec<String>,
+    ppids: Vec<u32>,
+    /// BSD `a`, listing the processes of every user
+    any_user: bool,
+    /// BSD `x`, listing processes without a terminal
+    any_tty: bool,
+    /// Whether BSD letters were given, which replace the default selection
+    bsd: bool,
+    /// The fields to order by, each with whether it is descending
+    sort: Vec<(Field, bool)>,
+    headers: bool,
 }
 
-fn get_fallback_processes(
-    _show_all: bool,
-    pid_filter: Option<u32>,
-) -> Result<Vec<ProcessInfo>, Box<dyn std::error::Error>> {
-    let mut processes = Vec::new();
-    let current_pid = std::process::id();
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            all: false,
+            format: Format::Default,
+            columns: Vec::new(),
+            pids: Vec::new(),
+            users: Vec::new(),
+            names: Vec::new(),
+            ppids: Vec::new(),
+            any_user: false,
+            any_tty: false,
+            bsd: false,
+            sort: Vec::new(),
+            headers: true,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--no-headers" | "--no-heading" => options.headers = false,
+                long if long.starts_with("--") => {
+                    let (name, attached) = match long.split_once('=') {
+                        Some((name, value)) => (name, Some(value.to_string())),
+                        None => (long, None),
+                    };
+                    if !matches!(name, "--pid" | "--user" | "--ppid" | "--sort" | "--format") {
+                        return Err(format!("unrecognized option '{long}'"));
+                    }
+                    let value = match attached {
+                        Some(value) => value,
+                        None => iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("option '{name}' requires an argument"))?,
+                    };
+                    options.apply(&name[2..], &value)?;
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'e' | 'A' => options.all = true,
+                            'f' => options.format = Format::Full,
+                            'o' | 'p' | 'u' | 'C' => {
+                                let value = match &short[2 + at..] {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    attached => attached.to_string(),
+                                };
+                                let name = match flag {
+                                    'o' => "format",
+                                    'p' => "pid",
+                                    'u' => "user",
+                                    _ => "command",
+                                };
+                                options.apply(name, &value)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                bsd => {
+                    options.bsd = true;
+                    for flag in bsd.chars() {
+                        match flag {
+                            'a' => options.any_user = true,
+                            'x' => options.any_tty = true,
+                            'u' => options.format = Format::User,
+                            'w' => {}
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(options)
+    }
 
-    if let Some(filter_pid) = pid_filter {
-        if filter_pid == current_pid {
-            processes.push(create_current_process_info());
+    /// Take the value of the option `name`
+    fn apply(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let items = value
+            .split([',', ' '])
+            .filter(|item| !item.is_empty())
+            .map(str::to_string);
+        match name {
+            "format" => {
+                for item in value.split(',').filter(|item| !item.is_empty()) {
+                    let (name, header) = match item.split_once('=') {
+                        Some((name, header)) => (name, Some(header)),
+                        None => (item, None),
+                    };
+                    let (field, default) = Field::lookup(name).ok_or_else(|| {
+                        format!("unknown user-defined format specifier \"{name}\"")
+                    })?;
+                    self.columns
+                        .push(Column::new(field, header.unwrap_or(default)));
+                }
+            }
+            "pid" | "ppid" => {
+                for item in items {
+                    let pid = item
+                        .parse()
+                        .map_err(|_| format!("invalid process ID '{item}'"))?;
+                    if name == "pid" {
+                        self.pids.push(pid);
+                    } else {
+                        self.ppids.push(pid);
+                    }
+                }
+            }
+            "user" => self.users.extend(items),
+            "command" => self.names.extend(items),
+            _ => {
+                for item in items {
+                    let (key, descending) = match item.strip_prefix('-') {
+                        Some(key) => (key, true),
+                        None => (item.strip_prefix('+').unwrap_or(&item), false),
+                    };
+                    let (field, _) = Field::lookup(key)
+                        .ok_or_else(|| format!("unknown sort specifier '{key}'"))?;
+                    self.sort.push((field, descending));
+                }
+            }
         }
-    } else {
-        processes.push(create_current_process_info());
+        Ok(())
     }
 
-    Ok(processes)
-}
+    /// Whether `-p`, `-u`, `-C` or `--ppid` was given
+    fn selected(&self) -> bool {
+        !(self.pids.is_empty()
+            && self.users.is_empty()
+            && self.names.is_empty()
+            && self.ppids.is_empty())
+    }
+
+    fn selects(&self, process: &ProcessEntry, me: &Me) -> bool {
+        if self.all {
+            true
+        } else if self.selected() {
+            self.pids.contains(&process.pid)
+                || self.ppids.contains(&process.ppid)
+                || self.users.contains(&process.user)
+                || self.names.contains(&process.name)
+        } else if self.bsd {
+            (self.any_user || process.user == me.user) && (self.any_tty || process.tty.is_some())
+        } else {
+            process.user == me.user && (me.tty.is_none() || process.tty == me.tty)
+        }
+    }
 
-fn create_current_process_info() -> ProcessInfo {
-    ProcessInfo {
-        pid: std::process::id(),
-        ppid: 0, // Parent PID not easily available in cross-platform way
-        user: whoami::username(),
-        command: std::env::current_exe()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| "nxsh".to_string()),
-        cpu_percent: 0.0,
-        mem_percent: 0.0,
-        virtual_size: 0,
-        resident_size: 0,
-        state: "R".to_string(),
-        start_time: "?".to_string(),
-        tty: "?".to_string(),
-        priority: 0,
-        nice: 0,
+    fn columns(&self) -> Vec<Column> {
+        if !self.columns.is_empty() {
+            return self.columns.clone();
+        }
+        let columns: &[(Field, &str)] = match self.format {
+            Format::Default => &[
+                (Field::Pid, "PID"),
+                (Field::Tty, "TTY"),
+                (Field::Time, "TIME"),
+                (Field::Comm, "CMD"),
+            ],
+            Format::Full => &[
+                (Field::User, "UID"),
+                (Field::Pid, "PID"),
+                (Field::Ppid, "PPID"),
+                (Field::Percent, "C"),
+                (Field::Start, "STIME"),
+                (Field::Tty, "TTY"),
+                (Field::Time, "TIME"),
+                (Field::Args, "CMD"),
+            ],
+            Format::User => &[
+                (Field::User, "USER"),
+                (Field::Pid, "PID"),
+                (Field::Cpu, "%CPU"),
+                (Field::Mem, "%MEM"),
+                (Field::Vsz, "VSZ"),
+                (Field::Rss, "RSS"),
+                (Field::Tty, "TTY"),
+                (Field::State, "STAT"),
+                (Field::Start, "START"),
+                (Field::Time, "TIME"),
+                (Field::Args, "COMMAND"),
+            ],
+        };
+        columns
+            .iter()
+            .map(|&(field, header)| Column::new(field, header))
+            .collect()
     }
 }
 
-fn display_processes(processes: &[ProcessInfo], show_full: bool, show_user_format: bool) {
-    if show_user_format {
-        println!(
-            "{:<8} {:>5} {:>4} {:>4} {:>6} {:>6} {:<8} {:<1} {:>8} {:>8} COMMAND",
-            "USER", "PID", "%CPU", "%MEM", "VSZ", "RSS", "TTY", "STAT", "START", "TIME"
-        );
-    } else {
-        println!("{:>5} {:<8} {:>8} CMD", "PID", "TTY", "TIME");
+/// Lay out the rows under their headers, numbers aligned right and text
+/// left, with the last column unpadded
+fn render(columns: &[Column], rows: &[Vec<String>], headers: bool) -> String {
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .map(|column| {
+            if headers {
+                column.header.chars().count()
+            } else {
+                0
+            }
+        })
+        .collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
     }
 
-    for process in processes {
-        if show_user_format {
-            let command = if show_full {
-                &process.command
-            } else {
-                // Show just the command name
-                process
-                    .command
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or(&process.command)
-            };
-
-            println!(
-                "{:<8} {:>5} {:>4.1} {:>4.1} {:>6} {:>6} {:<8} {:<1} {:>8} {:>8} {}",
-                truncate_string(&process.user, 8),
-                process.pid,
-                process.cpu_percent,
-                process.mem_percent,
-                format_size(process.virtual_size),
-                format_size(process.resident_size),
-                truncate_string(&process.tty, 8),
-                process.state,
-                process.start_time,
-                "00:00:00", // Time would need calculation
-                command
-            );
-        } else {
-            let command = if show_full {
-                &process.command
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|column| column.header.clone()).collect();
+    let lines = headers.then_some(&header).into_iter().chain(rows);
+    for cells in lines {
+        for (index, (cell, column)) in cells.iter().zip(columns).enumerate() {
+            if index > 0 {
+                out.push(' ');
+            }
+            let width = widths[index];
+            if column.field.numeric() {
+                let _ = write!(out, "{cell:>width$}");
+            } else if index + 1 == columns.len() {
+                out.push_str(cell);
             } else {
-                process
-                    .command
-                    .split('/')
-                    .next_back()
-                    .or_else(|| process.command.split('\\').next_back())
-                    .unwrap_or(&process.command)
-            };
-
-            println!(
-                "{:>5} {:<8} {:>8} {}",
-                process.pid,
-                truncate_string(&process.tty, 8),
-                "00:00:00",
-                command
-            );
+                let _ = write!(out, "{cell:<width$}");
+            }
         }
+        out.push('\n');
     }
+    out
 }
 
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}+", &s[..max_len - 1])
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
     }
-}
 
-fn format_size(size: u64) -> String {
-    if size < 1024 {
-        size.to_string()
-    } else if size < 1024 * 1024 {
-        format!("{}K", size / 1024)
-    } else if size < 1024 * 1024 * 1024 {
-        format!("{}M", size / (1024 * 1024))
-    } else {
-        format!("{}G", size / (1024 * 1024 * 1024))
+    #[test]
+    fn parses_columns_selections_and_sort_keys() {
+        let parsed = options(&["-o", "pid,comm=NAME", "-p1,2", "--sort", "-%cpu,+pid"]).unwrap();
+        assert_eq!(
+            parsed.columns(),
+            [
+                Column::new(Field::Pid, "PID"),
+                Column::new(Field::Comm, "NAME")
+            ]
+        );
+        assert_eq!(parsed.pids, [1, 2]);
+        assert_eq!(parsed.sort, [(Field::Cpu, true), (Field::Pid, false)]);
+
+        let aux = options(&["aux"]).unwrap();
+        assert!(aux.any_user && aux.any_tty);
+        assert_eq!(aux.format, Format::User);
+
+        assert_eq!(
+            options(&["-o", "bogus"]).unwrap_err(),
+            "unknown user-defined format specifier \"bogus\""
+        );
+        assert_eq!(
+            options(&["-p"]).unwrap_err(),
+            "option requires an argument -- 'p'"
+        );
     }
-}
 
-fn print_help() {
-    println!("Usage: ps [OPTIONS]");
-    println!("Display information about running processes.");
-    println!();
-    println!("Options:");
-    println!("  -a, --all       show processes for all users");
-    println!("  -f, --full      show full command lines");
-    println!("  -T, --threads   show threads");
-    println!("  -u, --user      show user-oriented format");
-    println!("  -p, --pid PID   show only process with specified PID");
-    println!("  -h, --help      display this help and exit");
-    println!();
-    println!("BSD-style options:");
-    println!("  aux             show all processes in user format");
-    println!();
-    println!("Examples:");
-    println!("  ps              Show processes for current user");
-    println!("  ps -a           Show all processes");
-    println!("  ps aux          Show all processes with detailed info");
-    println!("  ps -p 1234      Show process with PID 1234");
+    #[test]
+    fn aligns_numbers_right_and_text_left() {
+        let columns = [
+            Column::new(Field::Pid, "PID"),
+            Column::new(Field::Tty, "TTY"),
+            Column::new(Field::Comm, "CMD"),
+        ];
+        let rows = vec![
+            vec!["1".to_string(), "?".to_string(), "init".to_string()],
+            vec!["4242".to_string(), "pts/0".to_string(), "sh".to_string()],
+        ];
+        assert_eq!(
+            render(&columns, &rows, true),
+            " PID TTY   CMD\n   1 ?     init\n4242 pts/0 sh\n"
+        );
+        assert_eq!(cpu_time(Duration::from_secs(90_061)), "1-01:01:01");
+    }
 }
@@ -0,0 +1,751 @@
+//! Row queries for the structured pipeline commands
+//!
+//! `where` takes a small condition language over a table's columns:
+//!
+//!   where size -gt 1000 and type == file
+//!   where "name =~ .rs && not (size < 10 || size > 5000)"
+//!
+//! A comparison is `COLUMN OPERATOR VALUE`, and a bare `COLUMN` holds when
+//! the column is set and not empty, `false` or null. Comparisons combine
+//! with `and`/`&&`, `or`/`||`, `not`/`!` and parentheses, `and` binding
+//! tighter than `or`. The word operators (`-eq`, `-ne`, `-gt`, `-lt`, `-ge`,
+//! `-le`) save quoting `>` and `<` from the shell; a quoted VALUE is always
+//! text, an unquoted one is a number or boolean when it reads as one.
+//!
+//! `sort-by` keys and `group-by` aggregates are parsed here as well, and
+//! the paths `get` and `update` follow: field names and row or item
+//! indexes joined by dots (`user.addresses.0.city`), a segment in double
+//! quotes when the name itself holds a dot.
+
+use nxsh_core::StructuredValue;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+type Row = HashMap<String, StructuredValue>;
+
+/// A `where` condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        column: String,
+        operator: Operator,
+        value: StructuredValue,
+    },
+    Present(String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+}
+
+impl Operator {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word {
+            "==" | "=" | "-eq" => Self::Eq,
+            "!=" | "-ne" => Self::Ne,
+            ">" | "-gt" => Self::Gt,
+            "<" | "-lt" => Self::Lt,
+            ">=" | "-ge" => Self::Ge,
+            "<=" | "-le" => Self::Le,
+            "=~" | "contains" => Self::Contains,
+            "!~" => Self::NotContains,
+            "starts-with" => Self::StartsWith,
+            "ends-with" => Self::EndsWith,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A quoted word, which is never a keyword, operator or number
+    Quoted(String),
+    Operator(Operator),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+const SYMBOLS: &str = "=!<>~&|";
+
+impl Condition {
+    /// Parse a condition from `where`'s arguments
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        for arg in args {
+            lex(arg, &mut tokens)?;
+        }
+        if tokens.is_empty() {
+            return Err("usage: where CONDITION".to_string());
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let condition = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(condition),
+            Some(token) => Err(format!("{}: unexpected in condition", describe(token))),
+        }
+    }
+
+    /// Whether `row` satisfies the condition
+    pub fn matches(&self, row: &Row) -> bool {
+        match self {
+            Self::Compare {
+                column,
+                operator,
+                value,
+            } => row
+                .get(column)
+                .is_some_and(|field| compare(field, *operator, value)),
+            Self::Present(column) => row.get(column).is_some_and(|field| {
+                !matches!(
+                    field,
+                    StructuredValue::Nothing | StructuredValue::Bool(false)
+                ) && !field.to_string().is_empty()
+            }),
+            Self::Not(inner) => !inner.matches(row),
+            Self::And(left, right) => left.matches(row) && right.matches(row),
+            Self::Or(left, right) => left.matches(row) || right.matches(row),
+        }
+    }
+}
+
+fn compare(field: &StructuredValue, operator: Operator, value: &StructuredValue) -> bool {
+    let (text, wanted) = (field.to_string(), value.to_string());
+    match operator {
+        Operator::Eq => order(field, value) == Ordering::Equal,
+        Operator::Ne => order(field, value) != Ordering::Equal,
+        Operator::Gt => order(field, value) == Ordering::Greater,
+        Operator::Lt => order(field, value) == Ordering::Less,
+        Operator::Ge => order(field, value) != Ordering::Less,
+        Operator::Le => order(field, value) != Ordering::Greater,
+        Operator::Contains => text.contains(&wanted),
+        Operator::NotContains => !text.contains(&wanted),
+        Operator::StartsWith => text.starts_with(&wanted),
+        Operator::EndsWith => text.ends_with(&wanted),
+    }
+}
+
+/// Numbers by value, anything else by its text
+pub fn order(a: &StructuredValue, b: &StructuredValue) -> Ordering {
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn number(value: &StructuredValue) -> Option<f64> {
+    match value {
+        StructuredValue::Int(i) => Some(*i as f64),
+        StructuredValue::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// A condition operand: a number or boolean when it reads as one
+pub fn parse_value(text: &str) -> StructuredValue {
+    if let Ok(i) = text.parse::<i64>() {
+        StructuredValue::Int(i)
+    } else if let Ok(f) = text.parse::<f64>() {
+        StructuredValue::Float(f)
+    } else {
+        match text {
+            "true" => StructuredValue::Bool(true),
+            "false" => StructuredValue::Bool(false),
+            _ => StructuredValue::String(text.to_string()),
+        }
+    }
+}
+
+fn lex(arg: &str, tokens: &mut Vec<Token>) -> Result<(), String> {
+    let mut chars = arg.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        match c {
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err(format!("unterminated {c} in condition")),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            c if SYMBOLS.contains(c) => {
+                let mut symbol = String::new();
+                while let Some(&ch) = chars.peek().filter(|ch| SYMBOLS.contains(**ch)) {
+                    symbol.push(ch);
+                    chars.next();
+                }
+                tokens.push(match symbol.as_str() {
+                    "&&" => Token::And,
+                    "||" => Token::Or,
+                    "!" => Token::Not,
+                    _ => Token::Operator(
+                        Operator::parse(&symbol)
+                            .ok_or_else(|| format!("{symbol}: unknown operator"))?,
+                    ),
+                });
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "()'\"".contains(ch) || SYMBOLS.contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Operator::parse(&word).map_or(Token::Word(word), Token::Operator),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => word.clone(),
+        Token::Quoted(text) => format!("'{text}'"),
+        Token::Operator(operator) => format!("{operator:?}").to_lowercase(),
+        Token::And => "and".to_string(),
+        Token::Or => "or".to_string(),
+        Token::Not => "not".to_string(),
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos) == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.eat(&Token::Or) {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.not()?;
+        while self.eat(&Token::And) {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Condition, String> {
+        let column = match self.next() {
+            Some(Token::Open) => {
+                let condition = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing ) in condition".to_string());
+                }
+                return Ok(condition);
+            }
+            Some(Token::Word(word) | Token::Quoted(word)) => word,
+            Some(token) => return Err(format!("{}: expected a column", describe(&token))),
+            None => return Err("expected a column".to_string()),
+        };
+        let operator = match self.tokens.get(self.pos) {
+            Some(Token::Operator(operator)) => *operator,
+            None | Some(Token::And | Token::Or | Token::Close) => {
+                return Ok(Condition::Present(column))
+            }
+            Some(token) => return Err(format!("{}: unknown operator", describe(token))),
+        };
+        self.pos += 1;
+        let value = match self.next() {
+            Some(Token::Word(word)) => parse_value(&word),
+            Some(Token::Quoted(text)) => StructuredValue::String(text),
+            _ => return Err(format!("{column}: expected a value to compare with")),
+        };
+        Ok(Condition::Compare {
+            column,
+            operator,
+            value,
+        })
+    }
+}
+
+/// A `sort-by` key: `COLUMN`, or `COLUMN:desc` to sort that column downwards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl SortKey {
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let (column, descending) = match arg.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (arg, false),
+        };
+        if column.is_empty() {
+            return Err(format!("{arg}: invalid sort key"));
+        }
+        Ok(Self {
+            column: column.to_string(),
+            descending,
+        })
+    }
+
+    /// Compare rows by each key in turn; rows without the column sort first
+    pub fn compare(keys: &[SortKey], a: &Row, b: &Row) -> Ordering {
+        for key in keys {
+            let ordering = match (a.get(&key.column), b.get(&key.column)) {
+                (Some(x), Some(y)) => order(x, y),
+                (Some(_), None) => Ordering::Greater,
+                (None, Some(_)) => Ordering::Less,
+                (None, None) => Ordering::Equal,
+            };
+            let ordering = if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// A `group-by` aggregate over one column of each group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Aggregate {
+    /// The aggregate named by a `--sum`-style flag
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        Some(match flag {
+            "--sum" => Self::Sum,
+            "--min" => Self::Min,
+            "--max" => Self::Max,
+            "--avg" => Self::Avg,
+            _ => return None,
+        })
+    }
+
+    /// The result column for this aggregate over `column`, e.g. `size_sum`
+    pub fn column_name(self, column: &str) -> String {
+        let suffix = match self {
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Avg => "avg",
+        };
+        format!("{column}_{suffix}")
+    }
+
+    /// Aggregate `column` over `rows`; sum and avg skip non-numeric values,
+    /// and an empty result is null
+    pub fn compute(self, column: &str, rows: &[Row]) -> StructuredValue {
+        let values: Vec<&StructuredValue> = rows
+            .iter()
+            .filter_map(|row| row.get(column))
+            .filter(|value| !matches!(value, StructuredValue::Nothing))
+            .collect();
+        match self {
+            Self::Min => values
+                .into_iter()
+                .min_by(|a, b| order(a, b))
+                .cloned()
+                .unwrap_or(StructuredValue::Nothing),
+            Self::Max => values
+                .into_iter()
+                .max_by(|a, b| order(a, b))
+                .cloned()
+                .unwrap_or(StructuredValue::Nothing),
+            Self::Sum | Self::Avg => {
+                let numbers: Vec<&StructuredValue> =
+                    values.into_iter().filter(|v| number(v).is_some()).collect();
+                if numbers.is_empty() {
+                    return StructuredValue::Nothing;
+                }
+                let total: f64 = numbers.iter().filter_map(|v| number(v)).sum();
+                match self {
+                    Self::Avg => StructuredValue::Float(total / numbers.len() as f64),
+                    _ if numbers.iter().all(|v| matches!(v, StructuredValue::Int(_))) => {
+                        StructuredValue::Int(
+                            numbers
+                                .iter()
+                                .map(|v| match v {
+                                    StructuredValue::Int(i) => *i,
+                                    _ => 0,
+                                })
+                                .fold(0i64, i64::saturating_add),
+                        )
+                    }
+                    _ => StructuredValue::Float(total),
+                }
+            }
+        }
+    }
+}
+
+/// A step along a `get` or `update` path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Field(name) => f.write_str(name),
+            Self::Index(index) => write!(f, "{index}"),
+        }
+    }
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    loop {
+        let mut name = String::new();
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(format!("{path}: unterminated quote")),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek().filter(|c| **c != '.') {
+                name.push(c);
+                chars.next();
+            }
+        }
+        if name.is_empty() && !quoted {
+            return Err(format!("{path}: invalid path"));
+        }
+        segments.push(match name.parse() {
+            Ok(index) if !quoted => PathSegment::Index(index),
+            _ => PathSegment::Field(name),
+        });
+        match chars.next() {
+            None => return Ok(segments),
+            Some('.') => {}
+            Some(_) => return Err(format!("{path}: invalid path")),
+        }
+    }
+}
+
+/// The value at `path` in `value`. A field of a table or list is that
+/// field of each row, an index is a row or item
+pub fn get_path(value: &StructuredValue, path: &[PathSegment]) -> Result<StructuredValue, String> {
+    let Some((segment, rest)) = path.split_first() else {
+        return Ok(value.clone());
+    };
+    match (value, segment) {
+        (StructuredValue::Record(fields), PathSegment::Field(name)) => match fields.get(name) {
+            Some(field) => get_path(field, rest),
+            None => Err(format!("{name}: no such field")),
+        },
+        (StructuredValue::Table(rows), PathSegment::Index(index)) => match rows.get(*index) {
+            Some(row) => get_path(&StructuredValue::Record(row.clone()), rest),
+            None => Err(format!("{index}: no such row ({} rows)", rows.len())),
+        },
+        (StructuredValue::List(items), PathSegment::Index(index)) => match items.get(*index) {
+            Some(item) => get_path(item, rest),
+            None => Err(format!("{index}: no such item ({} items)", items.len())),
+        },
+        (StructuredValue::Table(rows), PathSegment::Field(name)) => {
+            if !rows.iter().any(|row| row.contains_key(name)) {
+                return Err(format!("{name}: no such column"));
+            }
+            let values = rows
+                .iter()
+                .map(|row| match row.get(name) {
+                    Some(field) => get_path(field, rest),
+                    None => Ok(StructuredValue::Nothing),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(StructuredValue::List(values))
+        }
+        (StructuredValue::List(items), PathSegment::Field(_)) => {
+            let values = items
+                .iter()
+                .map(|item| get_path(item, path))
+                .collect::<Result<_, _>>()?;
+            Ok(StructuredValue::List(values))
+        }
+        (other, segment) => Err(format!(
+            "{segment}: cannot follow into a {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// Set the value at `path` in `value`, adding missing fields along the way;
+/// a field of a table or list is set in each row
+pub fn set_path(
+    value: &mut StructuredValue,
+    path: &[PathSegment],
+    new: &StructuredValue,
+) -> Result<(), String> {
+    let Some((segment, rest)) = path.split_first() else {
+        *value = new.clone();
+        return Ok(());
+    };
+    match (value, segment) {
+        (StructuredValue::Record(fields), PathSegment::Field(name)) => set_path(
+            fields
+                .entry(name.clone())
+                .or_insert(StructuredValue::Nothing),
+            rest,
+            new,
+        ),
+        (StructuredValue::Table(rows), PathSegment::Index(index)) => {
+            let count = rows.len();
+            let row = rows
+                .get_mut(*index)
+                .ok_or_else(|| format!("{index}: no such row ({count} rows)"))?;
+            set_in_row(row, rest, new)
+        }
+        (StructuredValue::Table(rows), PathSegment::Field(_)) => rows
+            .iter_mut()
+            .try_for_each(|row| set_in_row(row, path, new)),
+        (StructuredValue::List(items), PathSegment::Index(index)) => {
+            let count = items.len();
+            let item = items
+                .get_mut(*index)
+                .ok_or_else(|| format!("{index}: no such item ({count} items)"))?;
+            set_path(item, rest, new)
+        }
+        (StructuredValue::List(items), PathSegment::Field(_)) => items
+            .iter_mut()
+            .try_for_each(|item| set_path(item, path, new)),
+        // A field added on the way holds the rest of the path
+        (value @ StructuredValue::Nothing, PathSegment::Field(_)) => {
+            *value = StructuredValue::Record(HashMap::new());
+            set_path(value, path, new)
+        }
+        (other, segment) => Err(format!(
+            "{segment}: cannot follow into a {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn set_in_row(row: &mut Row, path: &[PathSegment], new: &StructuredValue) -> Result<(), String> {
+    let mut record = StructuredValue::Record(std::mem::take(row));
+    let result = set_path(&mut record, path, new);
+    if let StructuredValue::Record(fields) = record {
+        *row = fields;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    fn row(name: &str, size: i64, kind: &str) -> Row {
+        HashMap::from([
+            ("name".to_string(), StructuredValue::String(name.into())),
+            ("size".to_string(), StructuredValue::Int(size)),
+            ("type".to_string(), StructuredValue::String(kind.into())),
+        ])
+    }
+
+    #[test]
+    fn operands_are_typed() {
+        assert_eq!(parse_value("42"), StructuredValue::Int(42));
+        assert_eq!(parse_value("1.5"), StructuredValue::Float(1.5));
+        assert_eq!(parse_value("true"), StructuredValue::Bool(true));
+        assert_eq!(
+            parse_value("main.rs"),
+            StructuredValue::String("main.rs".into())
+        );
+    }
+
+    #[test]
+    fn conditions_combine_with_precedence() {
+        let big_file = row("big.rs", 5000, "file");
+        let small_dir = row("src", 10, "dir");
+
+        let condition = Condition::parse(&args("size -gt 100 and type == file")).unwrap();
+        assert!(condition.matches(&big_file));
+        assert!(!condition.matches(&small_dir));
+
+        // `and` binds tighter than `or`
+        let condition = Condition::parse(&args("type == dir or size > 1 && name =~ .rs")).unwrap();
+        assert!(condition.matches(&big_file));
+        assert!(condition.matches(&small_dir));
+
+        let condition = Condition::parse(&["not (size<100 || name ends-with .rs)".into()]).unwrap();
+        assert!(!condition.matches(&big_file));
+        assert!(!condition.matches(&small_dir));
+
+        // A quoted operand stays text
+        let condition = Condition::parse(&args("name == '10'")).unwrap();
+        assert_eq!(
+            condition,
+            Condition::Compare {
+                column: "name".into(),
+                operator: Operator::Eq,
+                value: StructuredValue::String("10".into()),
+            }
+        );
+
+        assert_eq!(
+            Condition::parse(&args("size -between 1")).unwrap_err(),
+            "-between: unknown operator"
+        );
+        assert!(Condition::parse(&args("(size > 1")).is_err());
+    }
+
+    #[test]
+    fn sort_keys_and_aggregates() {
+        let rows = vec![row("a", 3, "file"), row("b", 3, "dir"), row("c", 1, "file")];
+        let keys = vec![
+            SortKey::parse("size:desc").unwrap(),
+            SortKey::parse("name").unwrap(),
+        ];
+        let mut sorted = rows.clone();
+        sorted.sort_by(|a, b| SortKey::compare(&keys, a, b));
+        let names: Vec<String> = sorted.iter().map(|r| r["name"].to_string()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        assert_eq!(
+            Aggregate::Sum.compute("size", &rows),
+            StructuredValue::Int(7)
+        );
+        assert_eq!(
+            Aggregate::Max.compute("name", &rows),
+            StructuredValue::String("c".into())
+        );
+        assert_eq!(
+            Aggregate::Avg.compute("size", &rows[..2]),
+            StructuredValue::Float(3.0)
+        );
+        assert_eq!(
+            Aggregate::Sum.compute("name", &rows),
+            StructuredValue::Nothing
+        );
+    }
+
+    #[test]
+    fn paths_get_and_set_nested_values() {
+        let path = parse_path("users.0.\"e.mail\"").unwrap();
+        assert_eq!(
+            path,
+            vec![
+                PathSegment::Field("users".into()),
+                PathSegment::Index(0),
+                PathSegment::Field("e.mail".into()),
+            ]
+        );
+        assert!(parse_path("a..b").is_err());
+
+        let mut value = StructuredValue::Record(HashMap::from([(
+            "users".to_string(),
+            StructuredValue::Table(vec![row("a", 1, "x"), row("b", 2, "y")]),
+        )]));
+        assert_eq!(
+            get_path(&value, &parse_path("users.1.name").unwrap()),
+            Ok(StructuredValue::String("b".into()))
+        );
+        assert_eq!(
+            get_path(&value, &parse_path("users.size").unwrap()),
+            Ok(StructuredValue::List(vec![
+                StructuredValue::Int(1),
+                StructuredValue::Int(2)
+            ]))
+        );
+        assert_eq!(
+            get_path(&value, &parse_path("users.5").unwrap()).unwrap_err(),
+            "5: no such row (2 rows)"
+        );
+
+        set_path(
+            &mut value,
+            &parse_path("users.meta.seen").unwrap(),
+            &StructuredValue::Bool(true),
+        )
+        .unwrap();
+        assert_eq!(
+            get_path(&value, &parse_path("users.0.meta.seen").unwrap()),
+            Ok(StructuredValue::Bool(true))
+        );
+        assert!(set_path(
+            &mut value,
+            &parse_path("users.0.name.x").unwrap(),
+            &StructuredValue::Nothing
+        )
+        .is_err());
+    }
+}
@@ -1,31 +1,489 @@
-//! `read` builtin  Eread a line from standard input into variable.
-//! Usage: read [-r] VAR
-//!   -r : do not treat backslash as escape, raw read (default raw)
+//! `read` builtin - read a line of input into shell variables
 //!
-//! If VAR is omitted, read into variable `REPLY`.
+//! Syntax: read [-rs] [-a ARRAY] [-d DELIM] [-n NCHARS] [-N NCHARS] [-p PROMPT] [-t TIMEOUT] [NAME...]
+//!
+//! The record is read from the shell's current stdin stream, up to DELIM
+//! (newline by default), and split into fields on `$IFS`. Each NAME gets one
+//! field and the last NAME gets the rest of the line. With no NAME the whole
+//! line is stored in `REPLY` untouched. Without `-r`, a backslash escapes the
+//! next character and backslash-newline continues the line.
+//!
+//! When the shell is interactive and stdin is a terminal, input goes through
+//! `nxsh_ui::readline::read_raw` so prompts, `-s`, `-n` and `-t` behave like
+//! they do in bash. Elsewhere a `-t` read takes its bytes from a helper
+//! thread, so a pipe or FIFO that stays quiet times out as well.
+//!
+//! Exit status: 0 on success, 1 on end of file, 128+SIGALRM on timeout and
+//! 2 on a usage error.
 
-use anyhow::Result;
-use nxsh_core::context::ShellContext;
-use std::io;
+use nxsh_core::context::{ShellArray, ShellContext};
+use nxsh_core::error::{ErrorKind, IoErrorKind, ShellError};
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_ui::readline::{read_raw, RawReadOptions, RawReadOutcome};
+use std::io::{self, IsTerminal, Read};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-pub fn read_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    let mut raw = true; // default raw (bash uses -r to disable escapes but we ignore escapes anyway)
-    let mut idx = 0;
-    if !args.is_empty() && args[0] == "-r" { raw = true; idx = 1; }
-    let var = args.get(idx).map(|s| s.as_str()).unwrap_or("REPLY");
+/// Status bash uses when `read -t` times out (128 + SIGALRM)
+const TIMEOUT_STATUS: i32 = 142;
+
+/// The `read` builtin command implementation
+pub struct ReadCommand;
+
+/// Parsed `read` options
+#[derive(Debug, Default)]
+struct ReadOptions {
+    raw: bool,
+    silent: bool,
+    array: Option<String>,
+    delimiter: Option<char>,
+    max_chars: Option<usize>,
+    exact_chars: bool,
+    prompt: Option<String>,
+    timeout: Option<Duration>,
+    names: Vec<String>,
+}
 
-    let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer)?;
-    if !raw {
-        // future escape handling
+/// How reading the record ended
+enum Record {
+    Complete(String),
+    Eof(String),
+    TimedOut(String),
+}
+
+/// What the next read of the stream gave
+enum Next {
+    Byte(u8),
+    End,
+    TimedOut,
+}
+
+impl Builtin for ReadCommand {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Read a line from standard input"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read one line from standard input, split it on IFS and assign the fields to the named \
+         variables (or REPLY), or to an array with -a."
+    }
+
+    fn usage(&self) -> &'static str {
+        "read [-rs] [-a array] [-d delim] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Read a line from standard input and split it into fields."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // read assigns shell variables
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match parse_options(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(2).with_error(
+                    format!("read: {message}\nread: usage: {}\n", self.usage()).into_bytes(),
+                ))
+            }
+        };
+        if let Some(bad) = options
+            .names
+            .iter()
+            .chain(options.array.iter())
+            .find(|n| !is_identifier(n))
+        {
+            return Ok(ExecutionResult::failure(1)
+                .with_error(format!("read: `{bad}': not a valid identifier\n").into_bytes()));
+        }
+
+        let record = if ctx.is_interactive() && std::io::stdin().is_terminal() {
+            read_terminal(&options)?
+        } else {
+            read_stream(ctx, &options)
+        };
+        let (line, status) = match record {
+            Record::Complete(line) => (line, 0),
+            Record::Eof(line) => (line, 1),
+            Record::TimedOut(line) => (line, TIMEOUT_STATUS),
+        };
+
+        assign_fields(ctx, &options, &line);
+        Ok(ExecutionResult::success(status))
+    }
+}
+
+impl ReadCommand {
+    /// Create a new read command instance
+    pub fn new() -> Self {
+        ReadCommand
+    }
+}
+
+impl Default for ReadCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<ReadOptions, String> {
+    let mut options = ReadOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if !arg.starts_with('-') || arg.len() == 1 {
+            break;
+        }
+        for (pos, letter) in arg[1..].char_indices() {
+            match letter {
+                'r' => options.raw = true,
+                's' => options.silent = true,
+                'a' | 'd' | 'n' | 'N' | 'p' | 't' => {
+                    // Option argument: rest of this word, or the next word
+                    let rest = &arg[1 + pos + letter.len_utf8()..];
+                    let value = if !rest.is_empty() {
+                        rest.to_string()
+                    } else {
+                        i += 1;
+                        args.get(i)
+                            .cloned()
+                            .ok_or_else(|| format!("-{letter}: option requires an argument"))?
+                    };
+                    apply_valued_option(&mut options, letter, value)?;
+                    break;
+                }
+                _ => return Err(format!("-{letter}: invalid option")),
+            }
+        }
+        i += 1;
+    }
+    options.names = args[i.min(args.len())..].to_vec();
+    Ok(options)
+}
+
+fn apply_valued_option(
+    options: &mut ReadOptions,
+    letter: char,
+    value: String,
+) -> Result<(), String> {
+    match letter {
+        'a' => options.array = Some(value),
+        // An empty delimiter means NUL, as in bash
+        'd' => options.delimiter = Some(value.chars().next().unwrap_or('\0')),
+        'n' | 'N' => {
+            let count = value
+                .parse::<usize>()
+                .map_err(|_| format!("{value}: invalid number"))?;
+            options.max_chars = Some(count);
+            options.exact_chars = letter == 'N';
+        }
+        'p' => options.prompt = Some(value),
+        't' => {
+            let secs = value
+                .parse::<f64>()
+                .ok()
+                .filter(|s| s.is_finite() && *s >= 0.0)
+                .ok_or_else(|| format!("{value}: invalid timeout specification"))?;
+            options.timeout = Some(Duration::from_secs_f64(secs.min(f64::from(u32::MAX))));
+        }
+        _ => unreachable!("only valued options reach here"),
     }
-    if buffer.ends_with('\n') { buffer.pop(); if buffer.ends_with('\r'){buffer.pop();} }
-    ctx.set_var(var, buffer);
     Ok(())
 }
 
+/// `-N` ignores the delimiter entirely
+fn effective_delimiter(options: &ReadOptions) -> Option<char> {
+    if options.exact_chars {
+        None
+    } else {
+        Some(options.delimiter.unwrap_or('\n'))
+    }
+}
+
+fn read_terminal(options: &ReadOptions) -> ShellResult<Record> {
+    let raw_options = RawReadOptions {
+        // With -N no character ends input early; NUL never arrives from a keyboard
+        delimiter: effective_delimiter(options).unwrap_or('\0'),
+        max_chars: options.max_chars,
+        echo: !options.silent,
+        timeout: options.timeout,
+    };
+    let prompt = options.prompt.as_deref().unwrap_or("");
+    let outcome = read_raw(prompt, &raw_options).map_err(|e| {
+        ShellError::new(
+            ErrorKind::IoError(IoErrorKind::FileReadError),
+            format!("read: {e}"),
+        )
+    })?;
+    Ok(match outcome {
+        RawReadOutcome::Complete(line) => Record::Complete(line),
+        RawReadOutcome::TimedOut(line) => Record::TimedOut(line),
+        RawReadOutcome::Eof(line) | RawReadOutcome::Interrupted(line) => Record::Eof(line),
+    })
+}
+
+/// Read the record from the context's stdin, giving up at the `-t` time
+/// limit however long the stream takes to answer
+fn read_stream(ctx: &mut ShellContext, options: &ReadOptions) -> Record {
+    let Some(timeout) = options.timeout else {
+        let mut byte = [0u8; 1];
+        return read_record(options, || match ctx.stdin.read(&mut byte) {
+            Ok(1) => Next::Byte(byte[0]),
+            _ => Next::End,
+        });
+    };
+    let deadline = Instant::now().checked_add(timeout);
+    let stdin = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+    let mut timed = TimedStdin::new(stdin);
+    let record = read_record(options, || timed.next_before(deadline));
+    ctx.stdin = timed.finish();
+    record
+}
+
+/// Read the record byte by byte so nothing past the delimiter is consumed
+fn read_record(options: &ReadOptions, mut next: impl FnMut() -> Next) -> Record {
+    let delimiter = effective_delimiter(options);
+    let mut bytes = Vec::new();
+    let mut chars = 0usize;
+    let mut escaped = false;
+    loop {
+        if options.max_chars.is_some_and(|max| chars >= max) {
+            break;
+        }
+        let b = match next() {
+            Next::Byte(b) => b,
+            Next::End => return Record::Eof(finish_bytes(bytes)),
+            Next::TimedOut => return Record::TimedOut(finish_bytes(bytes)),
+        };
+        if !options.raw && !escaped && b == b'\\' {
+            escaped = true;
+            bytes.push(b);
+            continue;
+        }
+        if escaped && b == b'\n' {
+            // Backslash-newline: line continuation
+            bytes.pop();
+            escaped = false;
+            continue;
+        }
+        if !escaped && delimiter.is_some_and(|d| d.is_ascii() && b == d as u8) {
+            break;
+        }
+        escaped = false;
+        bytes.push(b);
+        // Count characters, not UTF-8 continuation bytes
+        if b & 0xC0 != 0x80 {
+            chars += 1;
+        }
+    }
+    Record::Complete(finish_bytes(bytes))
+}
+
+fn finish_bytes(bytes: Vec<u8>) -> String {
+    let text = String::from_utf8_lossy(&bytes);
+    text.strip_suffix('\r').unwrap_or(&text).to_string()
+}
+
+/// The shell's stdin handed to a thread that reads it a byte at a time on
+/// request, so a read with a time limit can stop waiting on it
+struct TimedStdin {
+    requests: mpsc::Sender<()>,
+    bytes: mpsc::Receiver<Option<u8>>,
+    /// A byte has been asked for and not yet received
+    waiting: bool,
+    reader: JoinHandle<Box<dyn Read + Send>>,
+}
+
+impl TimedStdin {
+    fn new(mut stdin: Box<dyn Read + Send>) -> Self {
+        let (requests, asked) = mpsc::channel();
+        let (sent, bytes) = mpsc::channel();
+        let reader = thread::spawn(move || {
+            let mut byte = [0u8; 1];
+            while asked.recv().is_ok() {
+                let next = match stdin.read(&mut byte) {
+                    Ok(1) => Some(byte[0]),
+                    _ => None,
+                };
+                if sent.send(next).is_err() {
+                    break;
+                }
+            }
+            stdin
+        });
+        TimedStdin {
+            requests,
+            bytes,
+            waiting: false,
+            reader,
+        }
+    }
+
+    /// The next byte, unless `deadline` passes first; no deadline waits
+    /// as long as it takes
+    fn next_before(&mut self, deadline: Option<Instant>) -> Next {
+        if !self.waiting {
+            if self.requests.send(()).is_err() {
+                return Next::End;
+            }
+            self.waiting = true;
+        }
+        let next = match deadline {
+            Some(deadline) => {
+                match self
+                    .bytes
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Ok(next) => next,
+                    Err(RecvTimeoutError::Timeout) => return Next::TimedOut,
+                    Err(RecvTimeoutError::Disconnected) => None,
+                }
+            }
+            None => self.bytes.recv().ok().flatten(),
+        };
+        self.waiting = false;
+        next.map_or(Next::End, Next::Byte)
+    }
+
+    /// The stdin to read from next: the original once the thread is idle,
+    /// or this thread's stream while a byte asked for is still to come
+    fn finish(self) -> Box<dyn Read + Send> {
+        if self.waiting {
+            return Box::new(self);
+        }
+        let TimedStdin {
+            requests, reader, ..
+        } = self;
+        drop(requests);
+        reader.join().unwrap_or_else(|_| Box::new(io::empty()))
+    }
+}
+
+impl Read for TimedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.next_before(None) {
+            Next::Byte(b) => {
+                buf[0] = b;
+                Ok(1)
+            }
+            Next::End | Next::TimedOut => Ok(0),
+        }
+    }
+}
+
+/// The characters of a record, each marked when a backslash quoted it;
+/// with `-r` backslashes are ordinary characters
+fn quoted_chars(line: &str, raw: bool) -> Vec<(char, bool)> {
+    let mut out = Vec::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && !raw {
+            if let Some(next) = chars.next() {
+                out.push((next, true));
+            }
+        } else {
+            out.push((c, false));
+        }
+    }
+    out
+}
+
+fn text(chars: &[(char, bool)]) -> String {
+    chars.iter().map(|&(c, _)| c).collect()
+}
+
+fn assign_fields(ctx: &ShellContext, options: &ReadOptions, line: &str) {
+    let ifs = ctx.get_var("IFS").unwrap_or_else(|| " \t\n".to_string());
+    let line = quoted_chars(line, options.raw);
+    if let Some(array) = &options.array {
+        ctx.set_array(
+            array.clone(),
+            ShellArray::from_values(split_ifs(&line, &ifs, usize::MAX)),
+        );
+        return;
+    }
+    if options.names.is_empty() {
+        ctx.set_var("REPLY", text(&line));
+        return;
+    }
+    let fields = split_ifs(&line, &ifs, options.names.len());
+    for (i, name) in options.names.iter().enumerate() {
+        ctx.set_var(name.clone(), fields.get(i).cloned().unwrap_or_default());
+    }
+}
+
+/// Split `line` into at most `max` fields on IFS. Runs of IFS whitespace
+/// count as one separator and are trimmed from both ends; every other IFS
+/// character delimits exactly one field. The last field keeps the rest of
+/// the line, minus trailing IFS whitespace. Quoted characters never
+/// separate fields.
+fn split_ifs(line: &[(char, bool)], ifs: &str, max: usize) -> Vec<String> {
+    let is_sep = |&(c, quoted): &(char, bool)| !quoted && ifs.contains(c);
+    let is_ws = |p: &(char, bool)| is_sep(p) && p.0.is_whitespace();
+    let leading_ws = |s: &[(char, bool)]| s.iter().take_while(|p| is_ws(p)).count();
+    let mut fields = Vec::new();
+    let mut rest = &line[leading_ws(line)..];
+    while !rest.is_empty() {
+        if fields.len() + 1 == max {
+            let end = rest.iter().rposition(|p| !is_ws(p)).map_or(0, |i| i + 1);
+            fields.push(text(&rest[..end]));
+            break;
+        }
+        let end = rest.iter().position(is_sep).unwrap_or(rest.len());
+        fields.push(text(&rest[..end]));
+        // Consume one separator: surrounding whitespace plus at most one non-whitespace IFS char
+        rest = &rest[end..];
+        rest = &rest[leading_ws(rest)..];
+        if rest
+            .first()
+            .is_some_and(|p| is_sep(p) && !p.0.is_whitespace())
+        {
+            rest = &rest[1..];
+            rest = &rest[leading_ws(rest)..];
+        }
+    }
+    fields
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-} 
 
+    fn split(line: &str, ifs: &str, max: usize) -> Vec<String> {
+        split_ifs(&quoted_chars(line, false), ifs, max)
+    }
+
+    #[test]
+    fn splits_on_whitespace_and_keeps_remainder() {
+        assert_eq!(split("  a  b c  ", " \t\n", 2), vec!["a", "b c"]);
+        assert_eq!(split("a:b::c", ":", 4), vec!["a", "b", "", "c"]);
+        assert_eq!(split("", " ", 3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn quoted_separators_stay_in_their_field() {
+        assert_eq!(split("a\\ b c", " ", 3), vec!["a b", "c"]);
+        assert_eq!(split("x\\:y:z", ":", 3), vec!["x:y", "z"]);
+        assert_eq!(split("keep\\  ", " ", 1), vec!["keep "]);
+    }
+}
@@ -0,0 +1,249 @@
+//! `readlink` builtin - print where symbolic links point
+//!
+//! Syntax:
+//!   readlink [-f|-e|-m] [-n] [-q|-s|-v] [-z] FILE...
+//!
+//! Prints the target of each symbolic link FILE as it is stored. With `-f`
+//! FILE is instead resolved to an absolute path with every link followed,
+//! like `realpath`, needing all but the last component to exist; `-e`
+//! needs every component and `-m` none. `-n` leaves off the newline after a
+//! single FILE and `-z` ends each path with NUL. Failures are silent unless
+//! `-v` asks for messages.
+//!
+//! The exit status is 0 on success and 1 when any FILE is not a link or
+//! could not be resolved.
+
+use crate::common::{hal_message, BuiltinContext, BuiltinResult};
+use crate::realpath::canonicalize;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{FileSystem, ResolveMode};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The `readlink` builtin command implementation
+pub struct ReadlinkCommand;
+
+impl Builtin for ReadlinkCommand {
+    fn name(&self) -> &'static str {
+        "readlink"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print where symbolic links point"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the target of each symbolic link FILE, or with -f its fully resolved \
+         absolute path."
+    }
+
+    fn usage(&self) -> &'static str {
+        "readlink [-f|-e|-m] [-n] [-q|-s|-v] [-z] FILE..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Print where symbolic links point. Use 'readlink -f FILE' to follow every link \
+         to the final absolute path."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run readlink for the legacy dispatcher, writing straight to the
+/// process's standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    /// How to resolve FILE, or `None` to read the link itself
+    mode: Option<ResolveMode>,
+    no_newline: bool,
+    verbose: bool,
+    terminator: u8,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            mode: None,
+            no_newline: false,
+            verbose: false,
+            terminator: b'\n',
+            files: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let flags = match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--canonicalize" => "f",
+                "--canonicalize-existing" => "e",
+                "--canonicalize-missing" => "m",
+                "--no-newline" => "n",
+                "--quiet" | "--silent" => "q",
+                "--verbose" => "v",
+                "--zero" => "z",
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => &short[1..],
+                _ => {
+                    options.files.push(arg.clone());
+                    continue;
+                }
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'f' => options.mode = Some(ResolveMode::Parents),
+                    'e' => options.mode = Some(ResolveMode::Existing),
+                    'm' => options.mode = Some(ResolveMode::Missing),
+                    'n' => options.no_newline = true,
+                    'q' | 's' => options.verbose = false,
+                    'v' => options.verbose = true,
+                    'z' => options.terminator = b'\0',
+                    _ => return Err(format!("invalid option -- '{flag}'")),
+                }
+            }
+        }
+        if options.files.is_empty() {
+            return Err("missing operand".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn run(args: &[String], cwd: &Path) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let fail = |mut outcome: Outcome, message: String| {
+        outcome.stderr = format!("readlink: {message}\n").into_bytes();
+        outcome.status = 1;
+        outcome
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return fail(outcome, message),
+    };
+    let fs = match FileSystem::new() {
+        Ok(fs) => fs,
+        Err(e) => return fail(outcome, hal_message(&e)),
+    };
+    let mut terminate = true;
+    if options.no_newline {
+        if options.files.len() == 1 {
+            terminate = false;
+        } else {
+            outcome
+                .stderr
+                .extend(b"readlink: ignoring --no-newline with multiple arguments\n");
+        }
+    }
+
+    for name in &options.files {
+        let target = match options.mode {
+            Some(mode) => canonicalize(&fs, cwd, name, mode),
+            None => fs.read_link(cwd.join(name)).map_err(|e| hal_message(&e)),
+        };
+        match target {
+            Ok(target) => {
+                outcome.stdout.extend(target.to_string_lossy().as_bytes());
+                if terminate {
+                    outcome.stdout.push(options.terminator);
+                }
+            }
+            Err(message) => {
+                if options.verbose {
+                    outcome
+                        .stderr
+                        .extend(format!("readlink: {name}: {message}\n").as_bytes());
+                }
+                outcome.status = 1;
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readlink(args: &[&str], dir: &Path) -> (String, String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir);
+        (
+            String::from_utf8(outcome.stdout).unwrap(),
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    #[test]
+    fn canonicalizes_plain_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = FileSystem::new().unwrap();
+        let root = fs.resolve(dir.path(), ResolveMode::Existing).unwrap();
+        std::fs::write(root.join("file"), "").unwrap();
+
+        let file = root.join("file").display().to_string();
+        assert_eq!(readlink(&["-f", "./file"], &root).0, format!("{file}\n"));
+        assert_eq!(readlink(&["-fn", "file"], &root).0, file);
+        assert_eq!(readlink(&["-e", "missing"], &root).2, 1);
+        assert_eq!(readlink(&["-m", "x/y"], &root).2, 0);
+
+        // Not a link: silent without -v
+        assert_eq!(
+            readlink(&["file"], &root),
+            (String::new(), String::new(), 1)
+        );
+        assert!(readlink(&["-v", "file"], &root)
+            .1
+            .starts_with("readlink: file: "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_and_follows_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = FileSystem::new().unwrap();
+        let root = fs.resolve(dir.path(), ResolveMode::Existing).unwrap();
+        std::fs::create_dir(root.join("real")).unwrap();
+        std::os::unix::fs::symlink("real", root.join("one")).unwrap();
+        std::os::unix::fs::symlink("one", root.join("two")).unwrap();
+
+        assert_eq!(readlink(&["two", "one"], &root).0, "one\nreal\n");
+        assert_eq!(
+            readlink(&["-f", "two"], &root).0,
+            format!("{}\n", root.join("real").display())
+        );
+        let (out, err, _) = readlink(&["-n", "one", "two"], &root);
+        assert_eq!(out, "real\none\n");
+        assert!(err.contains("ignoring --no-newline"));
+    }
+}
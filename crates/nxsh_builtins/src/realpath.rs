@@ -0,0 +1,356 @@
+//! `realpath` builtin - print resolved absolute paths
+//!
+//! Syntax:
+//!   realpath [-e|-m] [-s] [-qz] [--relative-to=DIR] [--relative-base=DIR] FILE...
+//!
+//! Each FILE is made absolute, `.` and `..` are dropped and every symbolic
+//! link is followed. By default all but the last component must exist; `-e`
+//! requires the last one too and `-m` none of them. `-s` leaves symbolic
+//! links alone and only tidies the path, checking that it exists just with
+//! `-e`. `--relative-to` prints paths relative to DIR, and `--relative-base`
+//! does so only for paths under DIR, printing others in full. `-q` hides
+//! error messages and `-z` ends each path with NUL instead of a newline.
+//!
+//! Resolution goes through `nxsh_hal::fs`, so links are followed the same
+//! way on Unix and Windows, and Windows paths keep their drive letter form.
+//!
+//! The exit status is 0 on success and 1 when any FILE could not be resolved.
+
+use crate::common::{hal_message, io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{FileSystem, ResolveMode};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The `realpath` builtin command implementation
+pub struct RealpathCommand;
+
+impl Builtin for RealpathCommand {
+    fn name(&self) -> &'static str {
+        "realpath"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print resolved absolute paths"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print each FILE as an absolute path with symbolic links, '.' and '..' resolved, \
+         or relative to a directory with --relative-to."
+    }
+
+    fn usage(&self) -> &'static str {
+        "realpath [-e|-m] [-s] [-qz] [--relative-to=DIR] [--relative-base=DIR] FILE..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Print resolved absolute paths. Use 'realpath -m build/out' for a path that does \
+         not exist yet or 'realpath --relative-to=. FILE' for a relative one."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run realpath for the legacy dispatcher, writing straight to the
+/// process's standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// NAME made absolute against CWD with its symbolic links followed, or the
+/// message saying why it could not be
+pub(crate) fn canonicalize(
+    fs: &FileSystem,
+    cwd: &Path,
+    name: &str,
+    mode: ResolveMode,
+) -> Result<PathBuf, String> {
+    if name.is_empty() {
+        return Err(io_message(&io::ErrorKind::NotFound.into()));
+    }
+    fs.resolve(cwd.join(name), mode)
+        .map_err(|e| hal_message(&e))
+}
+
+/// NAME made absolute against CWD with `.` and `..` dropped by their text,
+/// leaving symbolic links alone
+fn normalize(cwd: &Path, name: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    for component in cwd.join(name).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            other => path.push(other),
+        }
+    }
+    path
+}
+
+/// PATH relative to the directory BASE, both absolute and resolved
+fn relative(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    // Paths on different Windows drives have no relative form
+    if path.first() != base.first() {
+        return path.iter().collect();
+    }
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut out = PathBuf::new();
+    for _ in common..base.len() {
+        out.push("..");
+    }
+    out.extend(&path[common..]);
+    if out.as_os_str().is_empty() {
+        out.push(".");
+    }
+    out
+}
+
+#[derive(Debug)]
+struct Options {
+    mode: ResolveMode,
+    follow_links: bool,
+    quiet: bool,
+    terminator: u8,
+    relative_to: Option<String>,
+    relative_base: Option<String>,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            mode: ResolveMode::Parents,
+            follow_links: true,
+            quiet: false,
+            terminator: b'\n',
+            relative_to: None,
+            relative_base: None,
+            files: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--canonicalize-existing" => options.mode = ResolveMode::Existing,
+                "--canonicalize-missing" => options.mode = ResolveMode::Missing,
+                "--strip" | "--no-symlinks" => options.follow_links = false,
+                "--physical" => options.follow_links = true,
+                "--quiet" => options.quiet = true,
+                "--zero" => options.terminator = b'\0',
+                "--relative-to" | "--relative-base" => {
+                    let dir = iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '{arg}' requires an argument"))?;
+                    match arg.as_str() {
+                        "--relative-to" => options.relative_to = Some(dir),
+                        _ => options.relative_base = Some(dir),
+                    }
+                }
+                long if long.starts_with("--relative-to=") => {
+                    options.relative_to = Some(long["--relative-to=".len()..].to_string());
+                }
+                long if long.starts_with("--relative-base=") => {
+                    options.relative_base = Some(long["--relative-base=".len()..].to_string());
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for flag in short[1..].chars() {
+                        match flag {
+                            'e' => options.mode = ResolveMode::Existing,
+                            'm' => options.mode = ResolveMode::Missing,
+                            's' => options.follow_links = false,
+                            'P' => options.follow_links = true,
+                            'q' => options.quiet = true,
+                            'z' => options.terminator = b'\0',
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        if options.files.is_empty() {
+            return Err("missing operand".to_string());
+        }
+        Ok(options)
+    }
+
+    /// NAME resolved as the options ask
+    fn locate(&self, fs: &FileSystem, cwd: &Path, name: &str) -> Result<PathBuf, String> {
+        if self.follow_links {
+            return canonicalize(fs, cwd, name, self.mode);
+        }
+        let path = normalize(cwd, name);
+        if self.mode == ResolveMode::Existing {
+            fs.symlink_metadata(&path).map_err(|e| hal_message(&e))?;
+        }
+        Ok(path)
+    }
+}
+
+fn run(args: &[String], cwd: &Path) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let fail = |mut outcome: Outcome, message: String| {
+        outcome.stderr = format!("realpath: {message}\n").into_bytes();
+        outcome.status = 1;
+        outcome
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return fail(outcome, message),
+    };
+    let fs = match FileSystem::new() {
+        Ok(fs) => fs,
+        Err(e) => return fail(outcome, hal_message(&e)),
+    };
+    let mut directories = [None, None];
+    for (slot, dir) in directories
+        .iter_mut()
+        .zip([&options.relative_to, &options.relative_base])
+    {
+        if let Some(dir) = dir {
+            match options.locate(&fs, cwd, dir) {
+                Ok(path) => *slot = Some(path),
+                Err(message) => return fail(outcome, format!("{dir}: {message}")),
+            }
+        }
+    }
+    let [relative_to, relative_base] = directories;
+
+    for name in &options.files {
+        let path = match options.locate(&fs, cwd, name) {
+            Ok(path) => path,
+            Err(message) => {
+                if !options.quiet {
+                    outcome
+                        .stderr
+                        .extend(format!("realpath: {name}: {message}\n").as_bytes());
+                }
+                outcome.status = 1;
+                continue;
+            }
+        };
+        // A base limits which paths are printed relative, and is what they
+        // are relative to when there is no --relative-to
+        let shown = match (&relative_to, &relative_base) {
+            (_, Some(base)) if !path.starts_with(base) => path,
+            (Some(to), Some(base)) if !to.starts_with(base) => path,
+            (Some(to), _) | (None, Some(to)) => relative(&path, to),
+            (None, None) => path,
+        };
+        outcome.stdout.extend(shown.to_string_lossy().as_bytes());
+        outcome.stdout.push(options.terminator);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn realpath(args: &[&str], dir: &Path) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir);
+        let text = if outcome.status == 0 {
+            outcome.stdout
+        } else {
+            outcome.stderr
+        };
+        (String::from_utf8(text).unwrap(), outcome.status)
+    }
+
+    /// A scratch directory, resolved in case the temporary directory is
+    /// itself reached through a link, holding `a/b/f`
+    fn scratch() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = FileSystem::new().unwrap();
+        let root = fs.resolve(dir.path(), ResolveMode::Existing).unwrap();
+        std::fs::create_dir_all(root.join("a").join("b")).unwrap();
+        std::fs::write(root.join("a").join("b").join("f"), "").unwrap();
+        (dir, root)
+    }
+
+    fn line(path: &Path) -> String {
+        format!("{}\n", path.display())
+    }
+
+    #[test]
+    fn resolves_dots_and_missing_parts() {
+        let (_dir, root) = scratch();
+        let f = root.join("a").join("b").join("f");
+        assert_eq!(realpath(&["a/./b/../b/f"], &root), (line(&f), 0));
+        assert_eq!(
+            realpath(&["a/new"], &root).0,
+            line(&root.join("a").join("new"))
+        );
+        assert_eq!(realpath(&["a/new/x"], &root).1, 1);
+        assert_eq!(
+            realpath(&["-m", "a/new/x/.."], &root).0,
+            line(&root.join("a").join("new"))
+        );
+        assert_eq!(
+            realpath(&["-e", "a/new"], &root),
+            ("realpath: a/new: No such file or directory\n".into(), 1)
+        );
+        assert_eq!(realpath(&["-q", "-e", "a/new"], &root), (String::new(), 1));
+        assert_eq!(realpath(&[""], &root).1, 1);
+    }
+
+    #[test]
+    fn prints_relative_paths() {
+        let (_dir, root) = scratch();
+        let args = ["--relative-to=a/b", "a/b/f", "a", "a/b"];
+        assert_eq!(realpath(&args, &root).0, "f\n..\n.\n");
+        assert_eq!(
+            realpath(&["--relative-to", "a/b", "-z", "a/c"], &root).0,
+            format!("..{}c\0", std::path::MAIN_SEPARATOR)
+        );
+        let outside = realpath(&["--relative-base=a/b", "a/b/f", "a"], &root).0;
+        assert_eq!(outside, format!("f\n{}", line(&root.join("a"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_links_unless_told_not_to() {
+        let (_dir, root) = scratch();
+        std::os::unix::fs::symlink("a/b", root.join("link")).unwrap();
+        assert_eq!(realpath(&["link/f"], &root).0, line(&root.join("a/b/f")));
+        assert_eq!(realpath(&["link/.."], &root).0, line(&root.join("a")));
+        assert_eq!(
+            realpath(&["-s", "link/./f"], &root).0,
+            line(&root.join("link/f"))
+        );
+        assert_eq!(realpath(&["-s", "link/.."], &root).0, line(&root));
+    }
+}
@@ -1,41 +1,140 @@
-//! `rev` command  Ereverse characters of each line.
+//! `rev` builtin - reverse the characters of each line
 //!
-//! Usage: rev [FILE...]
-//!   • With no FILE or FILE "-", reads standard input.
-//!   • Outputs each input line with characters reversed, preserving newline.
+//! Syntax:
+//!   rev [FILE...]
+//!
+//! Each line of the FILEs, standard input when there are none, is written
+//! with its characters in reverse order. Bytes that are not UTF-8 are kept
+//! as single characters.
+//!
+//! The exit status is 0 on success and 1 when a FILE cannot be read.
 
-use anyhow::Result;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use crate::common::operands::{lines, Inputs};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Read, Write};
 use std::path::Path;
 
-pub fn rev_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        rev_stream("-")?;
-    } else {
-        for p in args {
-            rev_stream(p)?;
-        }
+/// The `rev` builtin command implementation
+pub struct RevCommand;
+
+impl Builtin for RevCommand {
+    fn name(&self) -> &'static str {
+        "rev"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Reverse the characters of each line"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write each line of the FILEs with its characters in reverse order."
+    }
+
+    fn usage(&self) -> &'static str {
+        "rev [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Reverse the characters of each line. Use 'rev | cut -d. -f1 | rev' to take the \
+         last dot-separated field."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run rev for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// LINE with its characters reversed
+fn reverse(line: &[u8]) -> Vec<u8> {
+    let mut chars = Vec::new();
+    for chunk in line.utf8_chunks() {
+        chars.extend(chunk.valid().chars().map(|c| c.to_string().into_bytes()));
+        chars.extend(chunk.invalid().iter().map(|&byte| vec![byte]));
     }
-    Ok(())
+    chars.into_iter().rev().flatten().collect()
 }
 
-fn rev_stream(path: &str) -> Result<()> {
-    let mut reader: Box<dyn BufRead> = if path == "-" {
-        Box::new(BufReader::new(io::stdin()))
-    } else {
-        Box::new(BufReader::new(File::open(Path::new(path))?))
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
     };
-    let stdout = io::stdout();
-    let mut out = stdout.lock();
-    let mut line = String::new();
-    while reader.read_line(&mut line)? != 0 {
-        let mut core = line.trim_end_matches(&['\n','\r'][..]).chars().collect::<Vec<_>>();
-        core.reverse();
-        for ch in core { write!(out, "{}", ch)?; }
-        writeln!(out)?;
-        line.clear();
-    }
-    Ok(())
-} 
+    let mut files: Vec<&str> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                files.extend(iter.by_ref().map(String::as_str));
+                break;
+            }
+            option if option.len() > 1 && option.starts_with('-') => {
+                outcome.stderr = format!("rev: invalid option '{option}'\n").into_bytes();
+                outcome.status = 1;
+                return outcome;
+            }
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        files.push("-");
+    }
+    let mut inputs = Inputs::new(cwd, stdin);
+    for name in files {
+        let data = match inputs.read(name) {
+            Ok(data) => data,
+            Err(e) => {
+                outcome
+                    .stderr
+                    .extend(format!("rev: {name}: {}\n", io_message(&e)).as_bytes());
+                outcome.status = 1;
+                continue;
+            }
+        };
+        let complete = data.ends_with(b"\n");
+        let lines = lines(&data);
+        let count = lines.len();
+        for (index, line) in lines.into_iter().enumerate() {
+            outcome.stdout.extend(reverse(line));
+            if index + 1 < count || complete {
+                outcome.stdout.push(b'\n');
+            }
+        }
+    }
+    outcome
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverses_each_line() {
+        let outcome = run(&[], Path::new("."), &mut "abc\nhé!\nxy".as_bytes());
+        assert_eq!(outcome.stdout, "cba\n!éh\nyx".as_bytes());
+        assert_eq!(reverse(b"a\xffb"), b"b\xffa");
+    }
+}
@@ -1,13 +1,68 @@
 //! `rm` command - comprehensive file and directory removal implementation.
+//!
+//! Syntax:
+//!   rm [-dfiIrRv] [--trash] [--preserve-root[=all]] [--] FILE...
+//!
+//! `-i` asks before each removal and, with `-r`, before descending into
+//! each directory; `-I` asks once before removing more than three files or
+//! removing recursively. The questions go through [`Prompter`], so scripts
+//! can answer them with `NXSH_ASSUME`. `/` is never removed unless
+//! `--no-preserve-root` is given, and with `--preserve-root=all` neither
+//! is a FILE on another file system than its parent.
+//!
+//! With `--trash` files are moved to the desktop trash, as `trash put`
+//! does, instead of being deleted; directories still need `-r` or `-d`.
 
-use anyhow::{anyhow, Result};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crate::safety::Prompter;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 
-// Beautiful CUI design
-use crate::common::{BuiltinContext, BuiltinResult};
-use crate::ui_design::{ColorPalette, Colorize, Icons};
+pub struct RmCommand;
+
+impl Builtin for RmCommand {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Remove files or directories"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove each file, and with -r the directories and everything in them, or \
+         with --trash move them to the trash instead."
+    }
+
+    fn usage(&self) -> &'static str {
+        "rm [-dfiIrRv] [--trash] [--preserve-root[=all]] FILE..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove files or directories. Use 'rm -r DIR' for a directory and its \
+         contents, 'rm -i FILE...' to be asked about each, or 'rm --trash FILE' to \
+         keep it in the trash."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let assume = ctx.get_var("NXSH_ASSUME");
+        let mut prompter = Prompter::new(
+            "rm",
+            assume.as_deref(),
+            ctx.interactive,
+            &mut ctx.stdin,
+            &mut ctx.stderr,
+        );
+        let outcome = run(args, &cwd, &mut prompter);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
+    }
+}
 
 /// Options for rm command
 #[derive(Debug, Clone)]
@@ -17,8 +72,13 @@ pub struct RmOptions {
     pub recursive: bool,
     pub verbose: bool,
     pub preserve_root: bool,
+    /// `--preserve-root=all`: also keep FILEs on another file system than
+    /// their parent
+    pub preserve_root_all: bool,
     pub one_file_system: bool,
     pub dir: bool,
+    /// Move to the trash rather than delete
+    pub trash: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -36,221 +96,445 @@ impl Default for RmOptions {
             recursive: false,
             verbose: false,
             preserve_root: true,
+            preserve_root_all: false,
             one_file_system: false,
             dir: false,
+            trash: false,
         }
     }
 }
 
-/// Remove a file with the given options
-fn remove_file(path: &Path, options: &RmOptions) -> Result<()> {
-    if !path.exists() {
-        if !options.force {
-            return Err(anyhow!(
-                "cannot remove '{}': No such file or directory",
-                path.display()
-            ));
-        }
-        return Ok(());
+/// One run's removals, with what they printed
+struct Removal<'r, 'a> {
+    options: &'r RmOptions,
+    prompter: &'r mut Prompter<'a>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Removal<'_, '_> {
+    fn ask(&mut self, question: String) -> bool {
+        !matches!(self.options.interactive, InteractiveMode::Always)
+            || self.prompter.confirm(&question)
     }
 
-    // Interactive confirmation
-    if matches!(options.interactive, InteractiveMode::Always) {
-        print!("rm: remove regular file '{}'? ", path.display());
-        io::stdout().flush()?;
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        if !input.trim().starts_with('y') && !input.trim().starts_with('Y') {
-            return Ok(());
-        }
+    fn fail(&mut self, message: String) -> bool {
+        self.stderr.push_str(&format!("rm: {message}\n"));
+        false
     }
 
-    match fs::remove_file(path) {
-        Ok(()) => {
-            if options.verbose {
-                let palette = ColorPalette::new();
-                println!(
-                    "{} {} {}",
-                    Icons::FOLDER,
-                    "Removed file:".colorize(&palette.warning),
-                    path.display().to_string().colorize(&palette.primary)
-                );
+    /// Remove a file with the given options; false when it is still there
+    fn remove_file(&mut self, path: &Path, shown: &str) -> bool {
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            if self.options.force {
+                return true;
             }
+            return self.fail(format!(
+                "cannot remove '{shown}': No such file or directory"
+            ));
+        };
+
+        // Interactive confirmation
+        let kind = if meta.file_type().is_symlink() {
+            "symbolic link"
+        } else if meta.is_file() && meta.len() == 0 {
+            "regular empty file"
+        } else if meta.is_file() {
+            "regular file"
+        } else {
+            "file"
+        };
+        if !self.ask(format!("remove {kind} '{shown}'")) {
+            return false;
         }
-        Err(e) => {
-            return Err(anyhow!("cannot remove '{}': {}", path.display(), e));
+
+        match fs::remove_file(path) {
+            Ok(()) => {
+                if self.options.verbose {
+                    self.stdout.push_str(&format!("removed '{shown}'\n"));
+                }
+                true
+            }
+            Err(e) => self.fail(format!("cannot remove '{shown}': {}", io_message(&e))),
         }
     }
-    Ok(())
-}
 
-/// Remove a directory with the given options
-fn remove_directory(path: &Path, options: &RmOptions) -> Result<()> {
-    if !path.exists() {
-        if !options.force {
-            return Err(anyhow!(
-                "cannot remove '{}': No such file or directory",
-                path.display()
-            ));
+    /// Remove a directory with the given options; false when it is still
+    /// there, having kept something in it
+    fn remove_directory(&mut self, path: &Path, shown: &str) -> bool {
+        if !self.options.recursive && !self.options.dir {
+            return self.fail(format!("cannot remove '{shown}': Is a directory"));
         }
-        return Ok(());
-    }
 
-    if !options.recursive && !options.dir {
-        return Err(anyhow!(
-            "cannot remove '{}': Is a directory",
-            path.display()
-        ));
-    }
+        // Recursive removal
+        let mut emptied = true;
+        if self.options.recursive {
+            let empty = fs::read_dir(path).map(|mut entries| entries.next().is_none());
+            if empty.is_ok_and(|empty| !empty)
+                && !self.ask(format!("descend into directory '{shown}'"))
+            {
+                return false;
+            }
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(e) => return self.fail(format!("cannot remove '{shown}': {}", io_message(&e))),
+            };
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        emptied = self.fail(format!("cannot remove '{shown}': {}", io_message(&e)));
+                        continue;
+                    }
+                };
+                let entry_path = entry.path();
+                let entry_shown = Path::new(shown).join(entry.file_name());
+                let entry_shown = entry_shown.to_string_lossy();
 
-    // Recursive removal
-    if options.recursive {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let entry_path = entry.path();
+                let removed = if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    self.remove_directory(&entry_path, &entry_shown)
+                } else {
+                    self.remove_file(&entry_path, &entry_shown)
+                };
+                emptied &= removed;
+            }
+        }
+        // Whatever was kept keeps the directory too
+        if !emptied || !self.ask(format!("remove directory '{shown}'")) {
+            return false;
+        }
 
-            if entry_path.is_dir() {
-                remove_directory(&entry_path, options)?;
-            } else {
-                remove_file(&entry_path, options)?;
+        // Remove the directory itself
+        match fs::remove_dir(path) {
+            Ok(()) => {
+                if self.options.verbose {
+                    self.stdout
+                        .push_str(&format!("removed directory '{shown}'\n"));
+                }
+                true
             }
+            Err(e) => self.fail(format!(
+                "cannot remove directory '{shown}': {}",
+                io_message(&e)
+            )),
         }
     }
 
-    // Remove the directory itself
-    match fs::remove_dir(path) {
-        Ok(()) => {
-            if options.verbose {
-                let palette = ColorPalette::new();
-                println!(
-                    "{} {} {}",
-                    Icons::FOLDER,
-                    "Removed directory:".colorize(&palette.warning),
-                    path.display().to_string().colorize(&palette.primary)
-                );
+    /// Move a file or directory to the trash with the given options
+    fn trash(&mut self, path: &Path, shown: &str) -> bool {
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            if self.options.force {
+                return true;
             }
-        }
-        Err(e) => {
-            return Err(anyhow!(
-                "cannot remove directory '{}': {}",
-                path.display(),
-                e
+            return self.fail(format!(
+                "cannot remove '{shown}': No such file or directory"
             ));
+        };
+        if meta.is_dir() && !self.options.recursive && !self.options.dir {
+            return self.fail(format!("cannot remove '{shown}': Is a directory"));
+        }
+        if !self.ask(format!("move '{shown}' to the trash")) {
+            return false;
         }
+        if let Err(message) = crate::trash::put(path, shown) {
+            return self.fail(message);
+        }
+        if self.options.verbose {
+            self.stdout.push_str(&format!("trashed '{shown}'\n"));
+        }
+        true
     }
-    Ok(())
 }
 
 /// Parse command line arguments
-fn parse_args(args: &[String]) -> Result<(RmOptions, Vec<String>)> {
+fn parse_args(args: &[String]) -> Result<(RmOptions, Vec<String>), String> {
     let mut options = RmOptions::default();
     let mut files = Vec::new();
-    let mut i = 1;
-
-    while i < args.len() {
-        match args[i].as_str() {
-            "-f" | "--force" => options.force = true,
-            "-i" => options.interactive = InteractiveMode::Always,
-            "-I" => options.interactive = InteractiveMode::Once,
-            "-r" | "-R" | "--recursive" => options.recursive = true,
-            "-v" | "--verbose" => options.verbose = true,
-            "-d" | "--dir" => options.dir = true,
-            "--help" => {
-                print_help();
-                std::process::exit(0);
+    let mut options_done = false;
+
+    for arg in args {
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            files.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "--" => options_done = true,
+            "--force" => {
+                options.force = true;
+                options.interactive = InteractiveMode::Never;
+            }
+            "--interactive" | "--interactive=always" => {
+                options.interactive = InteractiveMode::Always
+            }
+            "--interactive=once" => options.interactive = InteractiveMode::Once,
+            "--interactive=never" => options.interactive = InteractiveMode::Never,
+            "--recursive" => options.recursive = true,
+            "--verbose" => options.verbose = true,
+            "--dir" => options.dir = true,
+            "--trash" => options.trash = true,
+            "--one-file-system" => options.one_file_system = true,
+            "--no-preserve-root" => {
+                options.preserve_root = false;
+                options.preserve_root_all = false;
+            }
+            "--preserve-root" => options.preserve_root = true,
+            "--preserve-root=all" => {
+                options.preserve_root = true;
+                options.preserve_root_all = true;
             }
-            arg if arg.starts_with('-') => {
-                return Err(anyhow!("invalid option: {}", arg));
+            long if long.starts_with("--") => {
+                return Err(format!("unrecognized option '{long}'"));
+            }
+            short => {
+                for flag in short.chars().skip(1) {
+                    match flag {
+                        'f' => {
+                            options.force = true;
+                            options.interactive = InteractiveMode::Never;
+                        }
+                        'i' => options.interactive = InteractiveMode::Always,
+                        'I' => options.interactive = InteractiveMode::Once,
+                        'r' | 'R' => options.recursive = true,
+                        'v' => options.verbose = true,
+                        'd' => options.dir = true,
+                        _ => return Err(format!("invalid option -- '{flag}'")),
+                    }
+                }
             }
-            _ => files.push(args[i].clone()),
         }
-        i += 1;
     }
 
-    if files.is_empty() {
-        return Err(anyhow!("missing operand"));
+    if files.is_empty() && !options.force {
+        return Err("missing operand".to_string());
     }
 
     Ok((options, files))
 }
 
-/// Print help message
-fn print_help() {
-    println!(
-        "rm - remove files and directories
-
-USAGE:
-    rm [OPTIONS] FILE...
-
-OPTIONS:
-    -f, --force               Ignore nonexistent files, never prompt
-    -i                        Prompt before every removal
-    -I                        Prompt once before removing more than three files
-    -r, -R, --recursive       Remove directories and their contents recursively
-    -v, --verbose             Explain what is being done
-    -d, --dir                 Remove empty directories
-    --help                    Display this help and exit
-
-EXAMPLES:
-    rm file.txt               Remove a file
-    rm -f file.txt            Force remove without prompting
-    rm -r directory/          Remove directory recursively
-    rm -rf temp/              Force remove directory
-    rm -i *.txt               Interactive removal
-    rm -v file1 file2         Verbose removal"
-    );
+/// Whether `path` is on another file system than the directory holding it
+#[cfg(unix)]
+fn on_other_device(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let parent = path.parent().unwrap_or(path);
+    match (fs::symlink_metadata(path), fs::metadata(parent)) {
+        (Ok(meta), Ok(parent)) => meta.is_dir() && meta.dev() != parent.dev(),
+        _ => false,
+    }
 }
 
-/// Execute the rm builtin command
+#[cfg(not(unix))]
+fn on_other_device(_path: &Path) -> bool {
+    false
+}
+
+/// Run rm for the legacy dispatcher, writing straight to the process's
+/// standard output and error
 pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    if args.is_empty() {
-        eprintln!("rm: missing operand");
-        return Ok(1);
-    }
+    let cwd = std::env::current_dir()?;
+    let assume = std::env::var("NXSH_ASSUME").ok();
+    let terminal = io::stdin().is_terminal();
+    let mut input = io::stdin().lock();
+    let mut questions = io::stderr();
+    let mut prompter = Prompter::new(
+        "rm",
+        assume.as_deref(),
+        terminal,
+        &mut input,
+        &mut questions,
+    );
+    let outcome = run(args, &cwd, &mut prompter);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
+}
 
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+fn run(args: &[String], cwd: &Path, prompter: &mut Prompter<'_>) -> Outcome {
     let (options, files) = match parse_args(args) {
-        Ok((opts, files)) => (opts, files),
-        Err(e) => {
-            eprintln!("rm: {e}");
-            return Ok(1);
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return Outcome {
+                stdout: String::new(),
+                stderr: format!("rm: {message}\n"),
+                status: 1,
+            }
         }
     };
 
     // Special handling for interactive mode "once"
-    if matches!(options.interactive, InteractiveMode::Once) && files.len() > 3 {
-        print!("rm: remove {} arguments? ", files.len());
-        io::stdout().flush().unwrap_or(());
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_ok()
-            && !input.trim().starts_with('y')
-            && !input.trim().starts_with('Y')
-        {
-            return Ok(0);
+    if matches!(options.interactive, InteractiveMode::Once)
+        && (files.len() > 3 || options.recursive)
+    {
+        let count = match files.len() {
+            1 => "1 argument".to_string(),
+            n => format!("{n} arguments"),
+        };
+        let how = if options.recursive {
+            " recursively"
+        } else {
+            ""
+        };
+        if !prompter.confirm(&format!("remove {count}{how}")) {
+            return Outcome {
+                stdout: String::new(),
+                stderr: String::new(),
+                status: 0,
+            };
         }
     }
 
-    for file in files {
-        let path = Path::new(&file);
+    let mut removal = Removal {
+        options: &options,
+        prompter,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    for file in &files {
+        let path = cwd.join(file);
 
         // Root protection
-        if options.preserve_root && path == Path::new("/") {
-            eprintln!("rm: it is dangerous to operate recursively on '/'");
+        if options.preserve_root && path.parent().is_none() {
+            removal.fail(format!(
+                "it is dangerous to operate recursively on '{file}'\n\
+                 rm: use --no-preserve-root to override this failsafe"
+            ));
+            continue;
+        }
+        if options.preserve_root_all && options.recursive && on_other_device(&path) {
+            removal.fail(format!(
+                "skipping '{file}', since it's on a different device\n\
+                 rm: and --preserve-root=all is in effect"
+            ));
+            continue;
+        }
+        if matches!(
+            file.trim_end_matches('/').rsplit('/').next(),
+            Some("." | "..")
+        ) {
+            removal.fail(format!(
+                "refusing to remove '.' or '..' directory: skipping '{file}'"
+            ));
             continue;
         }
 
-        let result = if path.is_dir() {
-            remove_directory(path, &options)
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_dir());
+        if options.trash {
+            removal.trash(&path, file);
+        } else if is_dir {
+            removal.remove_directory(&path, file);
         } else {
-            remove_file(path, &options)
-        };
-
-        if let Err(e) = result {
-            if !options.force {
-                eprintln!("rm: {e}");
-                return Ok(1);
-            }
+            removal.remove_file(&path, file);
         }
     }
 
-    Ok(0)
+    let status = if removal.stderr.is_empty() { 0 } else { 1 };
+    Outcome {
+        stdout: removal.stdout,
+        stderr: removal.stderr,
+        status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rm_answering(args: &[&str], cwd: &Path, answers: &str) -> (Outcome, String) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let mut input = answers.as_bytes();
+        let mut questions = Vec::new();
+        let mut prompter = Prompter::new("rm", None, false, &mut input, &mut questions);
+        let outcome = run(&args, cwd, &mut prompter);
+        (outcome, String::from_utf8(questions).unwrap())
+    }
+
+    fn rm(args: &[&str], cwd: &Path) -> Outcome {
+        rm_answering(args, cwd, "").0
+    }
+
+    #[test]
+    fn removes_files_and_directories_under_the_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("d/e")).unwrap();
+        fs::write(dir.path().join("d/e/f"), b"x").unwrap();
+        fs::write(dir.path().join("g"), b"x").unwrap();
+
+        let outcome = rm(&["-v", "g"], dir.path());
+        assert_eq!(outcome.status, 0, "{}", outcome.stderr);
+        assert_eq!(outcome.stdout, "removed 'g'\n");
+        assert!(!dir.path().join("g").exists());
+
+        let outcome = rm(&["d"], dir.path());
+        assert_eq!(outcome.status, 1);
+        assert_eq!(outcome.stderr, "rm: cannot remove 'd': Is a directory\n");
+
+        let outcome = rm(&["-rv", "d"], dir.path());
+        assert_eq!(outcome.status, 0, "{}", outcome.stderr);
+        assert_eq!(
+            outcome.stdout,
+            "removed 'd/e/f'\nremoved directory 'd/e'\nremoved directory 'd'\n"
+        );
+        assert!(!dir.path().join("d").exists());
+    }
+
+    #[test]
+    fn reports_missing_files_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        let outcome = rm(&["missing"], dir.path());
+        assert_eq!(outcome.status, 1);
+        assert_eq!(
+            outcome.stderr,
+            "rm: cannot remove 'missing': No such file or directory\n"
+        );
+        assert_eq!(rm(&["-f", "missing"], dir.path()).status, 0);
+        assert_eq!(rm(&[], dir.path()).stderr, "rm: missing operand\n");
+        assert_eq!(
+            rm(&["-q", "x"], dir.path()).stderr,
+            "rm: invalid option -- 'q'\n"
+        );
+        assert_eq!(
+            rm(&["-r", ".."], dir.path()).stderr,
+            "rm: refusing to remove '.' or '..' directory: skipping '..'\n"
+        );
+        assert_eq!(
+            rm(&["-d", "/"], dir.path()).stderr,
+            "rm: it is dangerous to operate recursively on '/'\n\
+             rm: use --no-preserve-root to override this failsafe\n"
+        );
+    }
+
+    #[test]
+    fn asks_before_each_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("d")).unwrap();
+        fs::write(dir.path().join("d/keep"), b"x").unwrap();
+        fs::write(dir.path().join("drop"), b"").unwrap();
+
+        // Keeping a file keeps its directory, without asking about it
+        let answers = "y\ny\nn\n";
+        let (outcome, questions) = rm_answering(&["-ri", "drop", "d"], dir.path(), answers);
+        assert_eq!(outcome.status, 0, "{}", outcome.stderr);
+        assert_eq!(
+            questions,
+            "rm: remove regular empty file 'drop'? rm: descend into directory 'd'? \
+             rm: remove regular file 'd/keep'? "
+        );
+        assert!(!dir.path().join("drop").exists());
+        assert!(dir.path().join("d/keep").exists());
+
+        // -I asks once, and no stops it all
+        let (outcome, questions) = rm_answering(&["-rI", "d"], dir.path(), "n\n");
+        assert_eq!(outcome.status, 0);
+        assert_eq!(questions, "rm: remove 1 argument recursively? ");
+        assert!(dir.path().join("d/keep").exists());
+        let (_, questions) = rm_answering(&["-rI", "d"], dir.path(), "y\n");
+        assert_eq!(questions, "rm: remove 1 argument recursively? ");
+        assert!(!dir.path().join("d").exists());
+    }
 }
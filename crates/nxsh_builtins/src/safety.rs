@@ -0,0 +1,320 @@
+//! Safety modes shared by `rm`, `cp` and `mv`
+//!
+//! [`Prompter`] asks the questions of `-i` and `-I`. In an interactive
+//! shell it asks on the terminal through [`nxsh_ui::consent::confirm`],
+//! where `a` answers yes and `q` no to the rest of the questions of the
+//! command; otherwise it writes the question to standard error and reads
+//! a line from the command's standard input, as `yes | rm -i *` expects,
+//! taking the end of input for no. Scripts can set `NXSH_ASSUME` to `yes`
+//! or `no` to have every question answered that way without asking.
+//!
+//! [`Overwrite`] is what `cp` and `mv` do about a destination that exists:
+//! replace it, skip it under `-n` or when `-u` finds it no older than the
+//! source, or ask under `-i`, and with `--backup` rename it out of the way
+//! first. Backups follow GNU: `--backup=CONTROL`, or `VERSION_CONTROL`,
+//! picks numbered `FILE.~N~` backups, simple `FILE~` ones with the suffix
+//! of `-S` or `SIMPLE_BACKUP_SUFFIX`, or numbered ones only for files that
+//! have them already.
+
+use crate::common::io_message;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Asks the questions of one command
+pub(crate) struct Prompter<'a> {
+    program: &'static str,
+    /// The answer `NXSH_ASSUME` gives every question
+    assume: Option<bool>,
+    /// Whether to ask on the terminal rather than read the input
+    terminal: bool,
+    input: &'a mut dyn Read,
+    out: &'a mut dyn Write,
+    /// The answer to the rest of the questions, once given
+    remembered: Option<bool>,
+}
+
+impl<'a> Prompter<'a> {
+    /// A prompter for `program`, answering as `assume`, the value of
+    /// `NXSH_ASSUME`, says when it is `yes` or `no`
+    pub(crate) fn new(
+        program: &'static str,
+        assume: Option<&str>,
+        terminal: bool,
+        input: &'a mut dyn Read,
+        out: &'a mut dyn Write,
+    ) -> Self {
+        let assume = match assume.map(|value| value.to_ascii_lowercase()) {
+            Some(value) if value == "yes" || value == "y" => Some(true),
+            Some(value) if value == "no" || value == "n" => Some(false),
+            _ => None,
+        };
+        Prompter {
+            program,
+            assume,
+            terminal,
+            input,
+            out,
+            remembered: None,
+        }
+    }
+
+    /// Whether the questions go to a terminal
+    pub(crate) fn on_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    /// Where the questions are written, for other lines meant for the user
+    /// rather than the output, such as progress
+    pub(crate) fn out(&mut self) -> &mut dyn Write {
+        &mut *self.out
+    }
+
+    /// Ask `question`, without its question mark, and return whether the
+    /// answer is yes
+    pub(crate) fn confirm(&mut self, question: &str) -> bool {
+        if let Some(answer) = self.remembered.or(self.assume) {
+            return answer;
+        }
+        let question = format!("{}: {question}", self.program);
+        if self.terminal {
+            use nxsh_ui::consent::Answer;
+            match nxsh_ui::consent::confirm(&question, &mut self.out) {
+                Ok(Some(Answer::Yes)) => return true,
+                Ok(Some(Answer::No)) => return false,
+                Ok(Some(Answer::YesToAll)) => {
+                    self.remembered = Some(true);
+                    return true;
+                }
+                Ok(Some(Answer::NoToAll)) => {
+                    self.remembered = Some(false);
+                    return false;
+                }
+                // No terminal after all: read the input instead
+                Ok(None) | Err(_) => {}
+            }
+        }
+        let _ = write!(self.out, "{question}? ");
+        let _ = self.out.flush();
+        // A byte at a time, so nothing after the line is used up
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while let Ok(1) = self.input.read(&mut byte) {
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+        }
+        matches!(
+            line.iter().find(|b| !b.is_ascii_whitespace()),
+            Some(b'y' | b'Y')
+        )
+    }
+}
+
+/// Which backups to make of files about to be replaced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupMode {
+    None,
+    Numbered,
+    Simple,
+    /// Numbered for files that have numbered backups, simple otherwise
+    Auto,
+}
+
+impl BackupMode {
+    /// The mode a `--backup=CONTROL` value names
+    pub(crate) fn parse(control: &str) -> Result<Self, String> {
+        match control {
+            "none" | "off" => Ok(BackupMode::None),
+            "numbered" | "t" => Ok(BackupMode::Numbered),
+            "existing" | "nil" => Ok(BackupMode::Auto),
+            "simple" | "never" => Ok(BackupMode::Simple),
+            _ => Err(format!("invalid argument '{control}' for '--backup'")),
+        }
+    }
+
+    /// The mode of `-b` or `--backup` without a value: `VERSION_CONTROL`,
+    /// or else `existing`
+    pub(crate) fn from_env() -> Result<Self, String> {
+        match std::env::var("VERSION_CONTROL") {
+            Ok(control) if !control.is_empty() => Self::parse(&control),
+            _ => Ok(BackupMode::Auto),
+        }
+    }
+}
+
+/// The suffix of simple backups when `-S` does not give one
+pub(crate) fn default_suffix() -> String {
+    std::env::var("SIMPLE_BACKUP_SUFFIX")
+        .ok()
+        .filter(|suffix| !suffix.is_empty() && !suffix.contains('/'))
+        .unwrap_or_else(|| "~".to_string())
+}
+
+/// The name a backup of `target` gets under `mode`, or `None` for none
+pub(crate) fn backup_path(target: &Path, mode: BackupMode, suffix: &str) -> Option<PathBuf> {
+    let with = |tail: &str| {
+        let mut name = OsString::from(target.as_os_str());
+        name.push(tail);
+        PathBuf::from(name)
+    };
+    let highest = || highest_backup(target);
+    match mode {
+        BackupMode::None => None,
+        BackupMode::Simple => Some(with(suffix)),
+        BackupMode::Numbered => Some(with(&format!(".~{}~", highest() + 1))),
+        BackupMode::Auto => match highest() {
+            0 => Some(with(suffix)),
+            n => Some(with(&format!(".~{}~", n + 1))),
+        },
+    }
+}
+
+/// The highest N of the `target.~N~` backups there are, or 0
+fn highest_backup(target: &Path) -> u64 {
+    let Some(name) = target.file_name().and_then(|name| name.to_str()) else {
+        return 0;
+    };
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let prefix = format!("{name}.~");
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.file_name();
+            let number = entry.to_str()?.strip_prefix(&prefix)?.strip_suffix('~')?;
+            number.parse::<u64>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// What `cp` and `mv` do about a destination that exists
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Overwrite {
+    pub(crate) clobber: Clobber,
+    /// `-u`: replace only files older than the source
+    pub(crate) update: bool,
+    pub(crate) backup: BackupMode,
+    pub(crate) suffix: String,
+}
+
+impl Default for Overwrite {
+    fn default() -> Self {
+        Overwrite {
+            clobber: Clobber::Replace,
+            update: false,
+            backup: BackupMode::None,
+            suffix: "~".to_string(),
+        }
+    }
+}
+
+/// Whether to replace, keep or ask about destinations; the last of `-f`,
+/// `-i` and `-n` given wins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Clobber {
+    Replace,
+    Ask,
+    Keep,
+}
+
+impl Overwrite {
+    /// Whether `source` may replace `target`, shown as `shown`, backing
+    /// the target up first when asked to. `Ok(false)` leaves it be.
+    pub(crate) fn allow(
+        &self,
+        source: &Path,
+        target: &Path,
+        shown: &str,
+        prompter: &mut Prompter<'_>,
+    ) -> Result<bool, String> {
+        let Ok(existing) = fs::symlink_metadata(target) else {
+            return Ok(true);
+        };
+        if self.clobber == Clobber::Keep {
+            return Ok(false);
+        }
+        if self.update {
+            let modified = |meta: io::Result<fs::Metadata>| meta.and_then(|meta| meta.modified());
+            if let (Ok(new), Ok(old)) = (modified(fs::metadata(source)), modified(Ok(existing))) {
+                if new <= old {
+                    return Ok(false);
+                }
+            }
+        }
+        if self.clobber == Clobber::Ask && !prompter.confirm(&format!("overwrite '{shown}'")) {
+            return Ok(false);
+        }
+        if let Some(backup) = backup_path(target, self.backup, &self.suffix) {
+            fs::rename(target, &backup)
+                .map_err(|e| format!("cannot back up '{shown}': {}", io_message(&e)))?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ask(input: &str, assume: Option<&str>, questions: usize) -> (Vec<bool>, String) {
+        let mut input = input.as_bytes();
+        let mut out = Vec::new();
+        let mut prompter = Prompter::new("rm", assume, false, &mut input, &mut out);
+        let answers = (0..questions)
+            .map(|n| prompter.confirm(&format!("remove 'f{n}'")))
+            .collect();
+        (answers, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn reads_answers_a_line_at_a_time() {
+        let (answers, out) = ask("y\nno\n  Yes please\n", None, 4);
+        assert_eq!(answers, [true, false, true, false]);
+        assert_eq!(
+            out,
+            "rm: remove 'f0'? rm: remove 'f1'? rm: remove 'f2'? rm: remove 'f3'? "
+        );
+        // Scripts can answer without any input
+        assert_eq!(ask("", Some("yes"), 2), (vec![true, true], String::new()));
+        assert_eq!(ask("y\n", Some("NO"), 1), (vec![false], String::new()));
+    }
+
+    #[test]
+    fn names_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("f");
+        fs::write(&target, b"x").unwrap();
+        let numbered = |n: u64| dir.path().join(format!("f.~{n}~"));
+
+        assert_eq!(backup_path(&target, BackupMode::None, "~"), None);
+        assert_eq!(
+            backup_path(&target, BackupMode::Simple, ".bak"),
+            Some(dir.path().join("f.bak"))
+        );
+        assert_eq!(
+            backup_path(&target, BackupMode::Auto, "~"),
+            Some(dir.path().join("f~"))
+        );
+        assert_eq!(
+            backup_path(&target, BackupMode::Numbered, "~"),
+            Some(numbered(1))
+        );
+        fs::write(numbered(1), b"x").unwrap();
+        fs::write(numbered(7), b"x").unwrap();
+        assert_eq!(
+            backup_path(&target, BackupMode::Auto, "~"),
+            Some(numbered(8))
+        );
+        assert_eq!(BackupMode::parse("nil"), Ok(BackupMode::Auto));
+        assert!(BackupMode::parse("sometimes").is_err());
+    }
+}
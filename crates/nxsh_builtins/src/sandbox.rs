@@ -0,0 +1,682 @@
+//! `sandbox` builtin - run a command under a sandbox profile
+//!
+//! Syntax:
+//!   sandbox run [-p PROFILE] [OPTION]... [--] COMMAND [ARG]...
+//!   sandbox profiles
+//!   sandbox show PROFILE
+//!
+//! `run` starts COMMAND with the restrictions of PROFILE, adjusted by the
+//! options, and waits for it; the shell itself is never restricted.
+//! `profiles` lists the profiles and `show` prints one as TOML.
+//!
+//! Options:
+//!   -p, --profile NAME     start from the profile NAME
+//!   -n, --no-network       refuse network sockets
+//!   -r, --read-only        make the file system read-only
+//!   -w, --writable PATH    keep PATH writable; implies -r elsewhere
+//!   -R, --readable PATH    hide everything but PATH and writable paths
+//!   -c, --cpu DURATION     limit the CPU time of each process
+//!   -m, --memory SIZE      limit the address space of each process
+//!   -t, --timeout DURATION kill COMMAND after DURATION
+//!   -f, --files N          limit open files per process
+//!   -u, --processes N      limit processes
+//!
+//! DURATION is a number of seconds with an optional `s`, `m`, `h` or `d`
+//! suffix and SIZE a number of bytes with an optional `K`, `M`, `G` or `T`
+//! suffix. Relative paths are taken from the working directory and `~/`
+//! from HOME.
+//!
+//! Profiles are tables under `profile` in `sandbox.toml` in the
+//! configuration directory (`$NXSH_CONFIG_DIR`, or `nexusshell` in the
+//! user's configuration directory), using the option names as keys:
+//!
+//!   [profile.build]
+//!   network = false
+//!   writable = ["./target", "/tmp"]
+//!   cpu = "10m"
+//!   memory = "4G"
+//!
+//! The built-in profiles `offline`, `readonly` and `strict` may be
+//! redefined there. Limits are enforced with resource limits, Landlock and
+//! seccomp on Linux and with a Job Object on Windows; a restriction the
+//! platform cannot enforce stops COMMAND from running.
+//!
+//! The exit status is COMMAND's. It is 124 when COMMAND times out, 125
+//! when the sandbox cannot be set up, 126 when COMMAND cannot be run, 127
+//! when it is not found and 2 on usage errors.
+
+use crate::common::{hal_message, io_message, parse_duration, BuiltinContext, BuiltinResult};
+use crate::env::{exported, find};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::sandbox::{self, SandboxPolicy};
+use nxsh_hal::HalError;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+const USAGE: &str = "sandbox run [-p PROFILE] [OPTION]... [--] COMMAND [ARG]... | sandbox profiles | sandbox show PROFILE";
+
+/// Status when COMMAND runs past its timeout
+const TIMED_OUT: i32 = 124;
+/// Status for failures of sandbox itself
+const CANCELED: i32 = 125;
+
+const BUILT_IN_PROFILES: &str = r#"
+[profile.offline]
+network = false
+
+[profile.readonly]
+writable = ["/dev/null"]
+
+[profile.strict]
+network = false
+writable = ["/dev/null"]
+cpu = "60s"
+memory = "1G"
+timeout = "5m"
+files = 256
+"#;
+
+/// The `sandbox` builtin command implementation
+pub struct SandboxCommand;
+
+impl Builtin for SandboxCommand {
+    fn name(&self) -> &'static str {
+        "sandbox"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run a command under a sandbox profile"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run COMMAND without network access, with a read-only or narrowed file system, \
+         and with CPU, memory and time limits, taken from a TOML profile and options."
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Contain a command. Use 'sandbox run --profile strict -- ./install.sh' to run a \
+         script offline on a read-only file system, or 'sandbox run -t 30s -m 512M -- make' \
+         for limits alone."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let env = exported(ctx);
+        let outcome = run(args, &env, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run sandbox for the legacy dispatcher
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let env: BTreeMap<String, String> = std::env::vars().collect();
+    let outcome = run(args, &env, &cwd);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn printed(text: String) -> Self {
+        Outcome {
+            stdout: text.into_bytes(),
+            stderr: Vec::new(),
+            status: 0,
+        }
+    }
+
+    fn failure(status: i32, message: String) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("sandbox: {message}\n").into_bytes(),
+            status,
+        }
+    }
+
+    fn usage(message: String) -> Self {
+        Self::failure(2, format!("{message}\nsandbox: usage: {USAGE}"))
+    }
+}
+
+/// What a profile restricts, with paths as written
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Profile {
+    no_network: bool,
+    writable: Option<Vec<String>>,
+    readable: Option<Vec<String>>,
+    cpu: Option<Duration>,
+    memory: Option<u64>,
+    timeout: Option<Duration>,
+    files: Option<u64>,
+    processes: Option<u64>,
+}
+
+impl Profile {
+    fn from_table(name: &str, table: &toml::Table) -> Result<Self, String> {
+        let mut profile = Profile::default();
+        for (key, value) in table {
+            let invalid = || format!("profile.{name}: invalid {key}");
+            match key.as_str() {
+                "network" => profile.no_network = !value.as_bool().ok_or_else(invalid)?,
+                "writable" | "readable" => {
+                    let paths = value
+                        .as_array()
+                        .and_then(|items| {
+                            items
+                                .iter()
+                                .map(|item| item.as_str().map(str::to_string))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .ok_or_else(invalid)?;
+                    if key == "writable" {
+                        profile.writable = Some(paths);
+                    } else {
+                        profile.readable = Some(paths);
+                    }
+                }
+                "cpu" | "timeout" => {
+                    let duration = match value {
+                        toml::Value::Integer(seconds) if *seconds >= 0 => {
+                            Duration::from_secs(*seconds as u64)
+                        }
+                        toml::Value::String(text) => parse_duration(text).map_err(|_| invalid())?,
+                        _ => return Err(invalid()),
+                    };
+                    if key == "cpu" {
+                        profile.cpu = Some(duration);
+                    } else {
+                        profile.timeout = Some(duration);
+                    }
+                }
+                "memory" => {
+                    profile.memory = Some(match value {
+                        toml::Value::Integer(bytes) if *bytes >= 0 => *bytes as u64,
+                        toml::Value::String(text) => parse_size(text).ok_or_else(invalid)?,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "files" | "processes" => {
+                    let count = value
+                        .as_integer()
+                        .and_then(|n| u64::try_from(n).ok())
+                        .ok_or_else(invalid)?;
+                    if key == "files" {
+                        profile.files = Some(count);
+                    } else {
+                        profile.processes = Some(count);
+                    }
+                }
+                _ => return Err(format!("profile.{name}: unknown setting '{key}'")),
+            }
+        }
+        Ok(profile)
+    }
+
+    /// The profile as a TOML table that reads back the same
+    fn to_toml(&self, name: &str) -> String {
+        let mut text = format!("[profile.{name}]\n");
+        if self.no_network {
+            text.push_str("network = false\n");
+        }
+        for (key, paths) in [("writable", &self.writable), ("readable", &self.readable)] {
+            if let Some(paths) = paths {
+                let quoted: Vec<String> = paths.iter().map(|p| format!("{p:?}")).collect();
+                text.push_str(&format!("{key} = [{}]\n", quoted.join(", ")));
+            }
+        }
+        if let Some(cpu) = self.cpu {
+            text.push_str(&format!("cpu = \"{}\"\n", show_duration(cpu)));
+        }
+        if let Some(memory) = self.memory {
+            text.push_str(&format!("memory = \"{}\"\n", show_size(memory)));
+        }
+        if let Some(timeout) = self.timeout {
+            text.push_str(&format!("timeout = \"{}\"\n", show_duration(timeout)));
+        }
+        if let Some(files) = self.files {
+            text.push_str(&format!("files = {files}\n"));
+        }
+        if let Some(processes) = self.processes {
+            text.push_str(&format!("processes = {processes}\n"));
+        }
+        text
+    }
+
+    /// A one-line description for `sandbox profiles`
+    fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.no_network {
+            parts.push("network off".to_string());
+        }
+        match &self.writable {
+            Some(paths) if paths.is_empty() => parts.push("read-only".to_string()),
+            Some(paths) => parts.push(format!("read-only except {}", paths.join(" "))),
+            None => {}
+        }
+        if let Some(paths) = &self.readable {
+            parts.push(format!("reads only {}", paths.join(" ")));
+        }
+        if let Some(cpu) = self.cpu {
+            parts.push(format!("cpu {}", show_duration(cpu)));
+        }
+        if let Some(memory) = self.memory {
+            parts.push(format!("memory {}", show_size(memory)));
+        }
+        if let Some(timeout) = self.timeout {
+            parts.push(format!("timeout {}", show_duration(timeout)));
+        }
+        if let Some(files) = self.files {
+            parts.push(format!("{files} files"));
+        }
+        if let Some(processes) = self.processes {
+            parts.push(format!("{processes} processes"));
+        }
+        if parts.is_empty() {
+            "no restrictions".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// The policy for the HAL, with paths resolved against CWD and HOME
+    fn policy(&self, cwd: &Path, home: Option<&str>) -> SandboxPolicy {
+        let resolve = |paths: &Vec<String>| -> Vec<PathBuf> {
+            paths
+                .iter()
+                .map(|path| match (home, path.strip_prefix("~/")) {
+                    (Some(home), Some(rest)) => Path::new(home).join(rest),
+                    (Some(home), None) if path == "~" => PathBuf::from(home),
+                    _ => cwd.join(path),
+                })
+                .collect()
+        };
+        SandboxPolicy {
+            no_network: self.no_network,
+            writable: self.writable.as_ref().map(resolve),
+            readable: self.readable.as_ref().map(resolve),
+            cpu_time: self.cpu,
+            memory: self.memory,
+            open_files: self.files,
+            processes: self.processes,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// The built-in profiles, then those of the configuration file by name
+fn load_profiles() -> Result<BTreeMap<String, Profile>, String> {
+    let mut profiles = parse_profiles(BUILT_IN_PROFILES)?;
+    let Some(file) = profile_file() else {
+        return Ok(profiles);
+    };
+    match std::fs::read_to_string(&file) {
+        Ok(text) => {
+            let own = parse_profiles(&text)
+                .map_err(|message| format!("{}: {message}", file.display()))?;
+            profiles.extend(own);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("{}: {}", file.display(), io_message(&e))),
+    }
+    Ok(profiles)
+}
+
+fn profile_file() -> Option<PathBuf> {
+    let base = match std::env::var_os("NXSH_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs_next::config_dir()?.join("nexusshell"),
+    };
+    Some(base.join("sandbox.toml"))
+}
+
+fn parse_profiles(text: &str) -> Result<BTreeMap<String, Profile>, String> {
+    let document: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| format!("invalid TOML: {}", e.message()))?;
+    let mut profiles = BTreeMap::new();
+    let Some(tables) = document.get("profile") else {
+        return Ok(profiles);
+    };
+    let tables = tables
+        .as_table()
+        .ok_or("'profile' must be a table of profiles")?;
+    for (name, table) in tables {
+        let table = table
+            .as_table()
+            .ok_or_else(|| format!("profile.{name} must be a table"))?;
+        profiles.insert(name.clone(), Profile::from_table(name, table)?);
+    }
+    Ok(profiles)
+}
+
+/// A number of bytes with an optional binary `K`, `M`, `G` or `T` suffix
+fn parse_size(text: &str) -> Option<u64> {
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number: u64 = text[..digits].parse().ok()?;
+    let power = match text[digits..].to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => return None,
+    };
+    number.checked_mul(1024u64.pow(power))
+}
+
+fn show_size(bytes: u64) -> String {
+    for (power, suffix) in [(4, "T"), (3, "G"), (2, "M"), (1, "K")] {
+        let unit = 1024u64.pow(power);
+        if bytes >= unit && bytes % unit == 0 {
+            return format!("{}{suffix}", bytes / unit);
+        }
+    }
+    bytes.to_string()
+}
+
+fn show_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis % 1000 != 0 {
+        return format!("{}s", duration.as_secs_f64());
+    }
+    let seconds = duration.as_secs();
+    match seconds {
+        0 => "0s".to_string(),
+        _ if seconds % 86400 == 0 => format!("{}d", seconds / 86400),
+        _ if seconds % 3600 == 0 => format!("{}h", seconds / 3600),
+        _ if seconds % 60 == 0 => format!("{}m", seconds / 60),
+        _ => format!("{seconds}s"),
+    }
+}
+
+/// `sandbox run` arguments
+#[derive(Debug, Default, PartialEq)]
+struct RunOptions {
+    profile: Option<String>,
+    changes: Profile,
+    read_only: bool,
+    command: Vec<String>,
+}
+
+impl RunOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = RunOptions::default();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |name: &str| {
+                inline
+                    .clone()
+                    .or_else(|| rest.next().cloned())
+                    .ok_or_else(|| format!("{name}: option requires an argument"))
+            };
+            let changes = &mut options.changes;
+            match name {
+                "--" => {
+                    options.command.extend(rest.cloned());
+                    break;
+                }
+                "-p" | "--profile" => options.profile = Some(value(name)?),
+                "-n" | "--no-network" => changes.no_network = true,
+                "-r" | "--read-only" => options.read_only = true,
+                "-w" | "--writable" => changes
+                    .writable
+                    .get_or_insert_with(Vec::new)
+                    .push(value(name)?),
+                "-R" | "--readable" => changes
+                    .readable
+                    .get_or_insert_with(Vec::new)
+                    .push(value(name)?),
+                "-c" | "--cpu" | "-t" | "--timeout" => {
+                    let text = value(name)?;
+                    let duration =
+                        parse_duration(&text).map_err(|_| format!("{text}: invalid duration"))?;
+                    if matches!(name, "-c" | "--cpu") {
+                        changes.cpu = Some(duration);
+                    } else {
+                        changes.timeout = Some(duration);
+                    }
+                }
+                "-m" | "--memory" => {
+                    let text = value(name)?;
+                    changes.memory =
+                        Some(parse_size(&text).ok_or_else(|| format!("{text}: invalid size"))?);
+                }
+                "-f" | "--files" | "-u" | "--processes" => {
+                    let text = value(name)?;
+                    let count = text
+                        .parse()
+                        .map_err(|_| format!("{text}: invalid number"))?;
+                    if matches!(name, "-f" | "--files") {
+                        changes.files = Some(count);
+                    } else {
+                        changes.processes = Some(count);
+                    }
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    return Err(format!("{option}: invalid option"));
+                }
+                _ => {
+                    options.command.push(arg.clone());
+                    options.command.extend(rest.cloned());
+                    break;
+                }
+            }
+        }
+        if options.command.is_empty() {
+            return Err("a command is required".to_string());
+        }
+        Ok(options)
+    }
+
+    /// The chosen profile with the options applied on top
+    fn apply(&self, mut profile: Profile) -> Profile {
+        let changes = &self.changes;
+        profile.no_network |= changes.no_network;
+        if self.read_only {
+            profile.writable.get_or_insert_with(Vec::new);
+        }
+        if let Some(paths) = &changes.writable {
+            profile
+                .writable
+                .get_or_insert_with(Vec::new)
+                .extend(paths.iter().cloned());
+        }
+        if let Some(paths) = &changes.readable {
+            profile
+                .readable
+                .get_or_insert_with(Vec::new)
+                .extend(paths.iter().cloned());
+        }
+        profile.cpu = changes.cpu.or(profile.cpu);
+        profile.memory = changes.memory.or(profile.memory);
+        profile.timeout = changes.timeout.or(profile.timeout);
+        profile.files = changes.files.or(profile.files);
+        profile.processes = changes.processes.or(profile.processes);
+        profile
+    }
+}
+
+fn run(args: &[String], env: &BTreeMap<String, String>, cwd: &Path) -> Outcome {
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Outcome::usage("a subcommand is required".to_string());
+    };
+    let profiles = match load_profiles() {
+        Ok(profiles) => profiles,
+        Err(message) => return Outcome::failure(CANCELED, message),
+    };
+    match subcommand.as_str() {
+        "profiles" => {
+            let width = profiles.keys().map(String::len).max().unwrap_or(0);
+            let listing: String = profiles
+                .iter()
+                .map(|(name, profile)| format!("{name:width$}  {}\n", profile.summary()))
+                .collect();
+            Outcome::printed(listing)
+        }
+        "show" => match rest {
+            [name] => match profiles.get(name) {
+                Some(profile) => Outcome::printed(profile.to_toml(name)),
+                None => Outcome::failure(CANCELED, format!("{name}: no such profile")),
+            },
+            _ => Outcome::usage("show takes one profile name".to_string()),
+        },
+        "run" => {
+            let options = match RunOptions::parse(rest) {
+                Ok(options) => options,
+                Err(message) => return Outcome::usage(message),
+            };
+            let base = match &options.profile {
+                Some(name) => match profiles.get(name) {
+                    Some(profile) => profile.clone(),
+                    None => return Outcome::failure(CANCELED, format!("{name}: no such profile")),
+                },
+                None => Profile::default(),
+            };
+            let profile = options.apply(base);
+            run_command(&profile, &options.command, env, cwd)
+        }
+        other => Outcome::usage(format!("{other}: unknown subcommand")),
+    }
+}
+
+fn run_command(
+    profile: &Profile,
+    command: &[String],
+    env: &BTreeMap<String, String>,
+    cwd: &Path,
+) -> Outcome {
+    let name = &command[0];
+    let Some(program) = find(name, env, cwd) else {
+        return Outcome::failure(127, format!("{name}: command not found"));
+    };
+    let mut child = Command::new(&program);
+    child
+        .args(&command[1..])
+        .current_dir(cwd)
+        .env_clear()
+        .envs(env);
+    let policy = profile.policy(cwd, env.get("HOME").map(String::as_str));
+    match sandbox::run(&policy, child) {
+        Ok(run) if run.timed_out => {
+            let limit = profile.timeout.map(show_duration).unwrap_or_default();
+            let mut stderr = run.stderr;
+            stderr.extend(format!("sandbox: {name}: timed out after {limit}\n").into_bytes());
+            Outcome {
+                stdout: run.stdout,
+                stderr,
+                status: TIMED_OUT,
+            }
+        }
+        Ok(run) => Outcome {
+            stdout: run.stdout,
+            stderr: run.stderr,
+            status: run.exit_code,
+        },
+        Err(HalError::Unsupported(message)) => {
+            Outcome::failure(CANCELED, format!("cannot enforce the profile: {message}"))
+        }
+        Err(HalError::Io(e)) if e.operation == "spawn" => {
+            let status = if e.kind == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+            Outcome::failure(status, format!("{name}: {}", hal_message(&HalError::Io(e))))
+        }
+        Err(e) => Outcome::failure(CANCELED, hal_message(&e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn built_in_profiles_read_back_from_toml() {
+        let profiles = parse_profiles(BUILT_IN_PROFILES).unwrap();
+        let strict = &profiles["strict"];
+        assert!(strict.no_network);
+        assert_eq!(strict.memory, Some(1 << 30));
+        assert_eq!(strict.timeout, Some(Duration::from_secs(300)));
+        let again = parse_profiles(&strict.to_toml("strict")).unwrap();
+        assert_eq!(&again["strict"], strict);
+        assert_eq!(
+            strict.summary(),
+            "network off, read-only except /dev/null, cpu 1m, memory 1G, timeout 5m, 256 files"
+        );
+    }
+
+    #[test]
+    fn options_adjust_the_profile() {
+        let options = RunOptions::parse(&args(&[
+            "-p",
+            "offline",
+            "-w",
+            "out",
+            "--memory=512M",
+            "--",
+            "make",
+            "-j4",
+        ]))
+        .unwrap();
+        assert_eq!(options.profile.as_deref(), Some("offline"));
+        assert_eq!(options.command, ["make", "-j4"]);
+        let base = Profile {
+            no_network: true,
+            ..Profile::default()
+        };
+        let profile = options.apply(base);
+        assert!(profile.no_network);
+        assert_eq!(profile.writable, Some(vec!["out".to_string()]));
+        assert_eq!(profile.memory, Some(512 << 20));
+
+        let policy = profile.policy(Path::new("/work"), Some("/home/u"));
+        assert_eq!(policy.writable, Some(vec![PathBuf::from("/work/out")]));
+    }
+
+    #[test]
+    fn rejects_bad_profiles_and_options() {
+        assert_eq!(
+            parse_profiles("[profile.x]\nnetwork = \"no\"\n"),
+            Err("profile.x: invalid network".to_string())
+        );
+        assert_eq!(
+            parse_profiles("[profile.x]\ndisk = 1\n"),
+            Err("profile.x: unknown setting 'disk'".to_string())
+        );
+        assert_eq!(
+            RunOptions::parse(&args(&["-m", "lots", "ls"])),
+            Err("lots: invalid size".to_string())
+        );
+        assert_eq!(
+            RunOptions::parse(&args(&["-n"])),
+            Err("a command is required".to_string())
+        );
+    }
+}
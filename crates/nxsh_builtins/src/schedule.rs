@@ -1,665 +1,1104 @@
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-use nxsh_core::advanced_scheduler::{AdvancedJobScheduler, SchedulerConfig};
-use tokio::runtime::Runtime;
-use once_cell::sync::OnceCell;
-use crate::common::i18n::t;
-
-/// Entry point for the `schedule` builtin
-pub fn schedule_cli(args: &[String]) -> Result<()> {
-    // Try external binary first (schedule, sched, or at)
-    for binary in &["schedule", "sched", "at"] {
-        if let Ok(path) = which(binary) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("schedule: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
-        }
+//! `schedule`, `at` and `cron` builtins - run commands later from the shell's job scheduler
+//!
+//! Syntax:
+//!   schedule [list]
+//!   schedule add [-Dn] (--at TIME | --in DURATION | --every DURATION | --cron EXPR) COMMAND...
+//!   schedule (remove | enable | disable) ID...
+//!   at [-D] TIME [COMMAND...]
+//!   at (-l | -r ID...)
+//!   cron [-Dn] EXPR COMMAND...
+//!   cron (-l | -r ID...)
+//!
+//! `at` runs COMMAND once at TIME, reading it from standard input when no
+//! COMMAND is given. `cron` runs COMMAND whenever the cron expression EXPR
+//! matches, and `schedule` does either, or runs COMMAND every DURATION.
+//! `-l` and `schedule list` show each job's ID and next run, `-r` and
+//! `schedule remove` drop jobs, and `schedule disable` keeps a job without
+//! running it until `schedule enable`.
+//!
+//! A COMMAND of one word is a command line of its own, so `at 18:00 'make >
+//! build.log'` keeps its redirection for the job; several words are quoted
+//! and joined.
+//!
+//! TIME is `now`, `noon`, `midnight` or a clock time `HH:MM` with an
+//! optional `am` or `pm`, then optionally `today`, `tomorrow` or a date
+//! `YYYY-MM-DD`, then optionally `+ N UNIT` with UNIT one of minutes, hours,
+//! days and weeks; a clock time that has passed today means tomorrow. A
+//! DURATION is a number with a suffix of `s`, `m`, `h`, `d` or `w`, seconds
+//! when it has none. EXPR has five fields, minute hour day month weekday,
+//! read in local time, or is one of `@hourly`, `@daily`, `@weekly`,
+//! `@monthly` and `@yearly`; it needs quoting unless given as five words.
+//!
+//! Jobs are kept in `schedule.json` in the NexusShell configuration
+//! directory (`$NXSH_CONFIG_DIR`, or `nexusshell` in the platform's), so
+//! they outlive the shell. An interactive shell runs them, with `nxsh -c`
+//! in the directory they were scheduled from; when several are open the
+//! first one does and the others take over when it exits. Jobs that fell
+//! due while no shell was running run when the next one starts.
+//!
+//! When a job fails, or finishes with `-n` or from `at`, its status and
+//! the start of its output are shown before the next prompt. `-D` also
+//! shows them as a desktop notification through `notify-send`, or
+//! `osascript` on macOS.
+//!
+//! The exit status is 0 on success, 1 for bad usage or unknown jobs and 2
+//! when the job table cannot be read or written.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate, NaiveTime, TimeZone};
+use fs2::FileExt;
+use nxsh_core::advanced_scheduler::{
+    self, AdvancedJobScheduler, JobExecutionResult, JobNotification, JobSchedule, SchedulerConfig,
+};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use once_cell::sync::{Lazy, OnceCell};
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, SystemTime};
+
+/// The status for a job table that cannot be read or written
+const BROKEN: i32 = 2;
+
+/// The longest delay or interval taken, a century in seconds
+const LONGEST: u64 = 100 * 366 * 86400;
+
+/// How many lines of a job's output its notice shows
+const SHOWN_LINES: usize = 10;
+
+/// The `schedule` builtin command implementation
+pub struct ScheduleCommand;
+
+/// The `at` builtin command implementation
+pub struct AtCommand;
+
+/// The `cron` builtin command implementation
+pub struct CronCommand;
+
+impl Builtin for ScheduleCommand {
+    fn name(&self) -> &'static str {
+        "schedule"
     }
 
-    // Enhanced interactive fallback when no args provided
-    if args.is_empty() {
-        return interactive_schedule_guide();
+    fn synopsis(&self) -> &'static str {
+        "Run commands later, once or repeatedly"
     }
 
-    match args[0].as_str() {
-        "-l" | "--list" => {
-            let (rt, sched) = ensure_scheduler()?;
-            let jobs = rt.block_on(async { sched.list_jobs().await });
-            if jobs.is_empty() {
-                println!("{}", t("schedule-no-tasks"));
-            } else {
-                for job in jobs {
-                    let (when, kind) = match job.schedule {
-                        nxsh_core::advanced_scheduler::JobSchedule::Once { run_at } => (run_at, "once"),
-                        nxsh_core::advanced_scheduler::JobSchedule::Recurring { next_run, .. } => (next_run, "cron"),
-                        nxsh_core::advanced_scheduler::JobSchedule::Interval { next_run, .. } => (next_run, "interval"),
-                        nxsh_core::advanced_scheduler::JobSchedule::EventBased { .. } => (std::time::SystemTime::UNIX_EPOCH, "event"),
-                    };
-                    let ts = match when.duration_since(std::time::SystemTime::UNIX_EPOCH) { Ok(d) => d.as_secs(), Err(_) => 0 };
-                    println!("{:<14} {:<6} {:<8} {}", job.id, kind, ts, job.command);
-                }
+    fn description(&self) -> &'static str {
+        "Add, list, remove, enable and disable jobs that run a command once at a time, \
+         after a delay, every interval or on a cron schedule. Jobs are kept across shell \
+         restarts and their results are shown before the prompt."
+    }
+
+    fn usage(&self) -> &'static str {
+        "schedule [list]\n\
+         schedule add [-Dn] (--at TIME | --in DURATION | --every DURATION | --cron EXPR) \
+         COMMAND...\n\
+         schedule (remove | enable | disable) ID..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Schedule commands. Use 'schedule add --in 10m notify-me' for a reminder, \
+         'schedule add --every 1h -n df -h' to watch a disk or 'schedule' to list \
+         the jobs and their next runs."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Schedule, args)
+    }
+}
+
+impl Builtin for AtCommand {
+    fn name(&self) -> &'static str {
+        "at"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run a command once at a given time"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run COMMAND, or the commands read from standard input, once at TIME; -l lists \
+         the pending jobs and -r removes them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "at [-D] TIME [COMMAND...]\n\
+         at (-l | -r ID...)"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run a command later. Use 'at 18:00 make' to build at six, \
+         'at now + 20 minutes echo tea' for a reminder or 'at -l' to list the jobs."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::At, args)
+    }
+}
+
+impl Builtin for CronCommand {
+    fn name(&self) -> &'static str {
+        "cron"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Run a command on a cron schedule"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run COMMAND whenever the five-field cron expression EXPR matches, in local \
+         time; -l lists the recurring jobs and -r removes them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "cron [-Dn] EXPR COMMAND...\n\
+         cron (-l | -r ID...)"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run commands periodically. Use \"cron '0 3 * * *' backup.sh\" for a nightly \
+         backup, \"cron -n '*/15 9-17 * * mon-fri' fetch-mail\" during work hours or \
+         'cron -l' to list the jobs."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Cron, args)
+    }
+}
+
+fn execute_in(
+    ctx: &mut ShellContext,
+    flavor: Flavor,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let cwd = ctx.cwd.clone();
+    let outcome = run(flavor, args, &cwd, &mut ctx.stdin);
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+/// Run schedule for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Schedule, args)
+}
+
+/// Run at for the legacy dispatcher
+pub fn execute_at(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::At, args)
+}
+
+/// Run cron for the legacy dispatcher
+pub fn execute_cron(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Cron, args)
+}
+
+fn run_legacy(flavor: Flavor, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(flavor, args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Let this shell run the scheduled jobs when no other shell does, and take
+/// the notices of jobs that finished since the last call
+///
+/// The interactive shell calls this before each prompt. The first call
+/// starts a thread that runs the jobs for as long as this shell holds the
+/// lock on the job table, trying again every second while another shell
+/// does.
+pub fn poll() -> Vec<String> {
+    static RUNNER: Once = Once::new();
+    RUNNER.call_once(|| {
+        if let Ok(mut config) = config() {
+            // Jobs run in NexusShell itself, so that builtins and functions work
+            if let Ok(shell) = std::env::current_exe() {
+                config.shell = vec![shell.to_string_lossy().into_owned(), "-c".to_string()];
             }
+            let _ = std::thread::Builder::new()
+                .name("nxsh-scheduler".to_string())
+                .spawn(move || run_jobs(config));
         }
-        "-d" | "--delete" => {
-            if args.len() < 2 {
-                eprintln!("{}", t("schedule-delete-missing-id"));
-                std::process::exit(1);
+    });
+    NOTICES
+        .lock()
+        .map(|mut notices| notices.drain(..).collect())
+        .unwrap_or_default()
+}
+
+/// Notices waiting for the next prompt
+static NOTICES: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Run the jobs once this process holds the lock on the job table
+fn run_jobs(config: SchedulerConfig) {
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return;
+    };
+    let Some(lock_path) = config.state_file.as_ref().map(|f| f.with_extension("lock")) else {
+        return;
+    };
+    runtime.block_on(async move {
+        // The lock stays open for as long as the scheduler runs
+        let mut running: Option<(File, AdvancedJobScheduler)> = None;
+        loop {
+            if running.is_none() {
+                if let Some(lock) = take_lock(&lock_path) {
+                    let mut scheduler = AdvancedJobScheduler::new(config.clone());
+                    if scheduler.start().await.is_ok() {
+                        running = Some((lock, scheduler));
+                    }
+                }
+            }
+            if let Some((_, scheduler)) = &running {
+                for notification in scheduler.take_notifications() {
+                    deliver(&notification);
+                }
             }
-            let job_id = &args[1];
-            let (rt, sched) = ensure_scheduler()?;
-            let ok = rt.block_on(async { sched.cancel_job(job_id).await })?;
-            if ok { println!("{}", t("schedule-deleted")); } else { eprintln!("{}: {job_id}", t("schedule-job-not-found")); std::process::exit(1); }
+            tokio::time::sleep(Duration::from_secs(1)).await;
         }
-        "--stats" => {
-            let (rt, sched) = ensure_scheduler()?;
-            let s = rt.block_on(async { sched.get_statistics().await });
-            println!("{} {}", t("schedule-stats-total"), s.total_jobs);
-            println!("{} {}", t("schedule-stats-running"), s.running_jobs);
-            println!("{} {}", t("schedule-stats-queued"), s.queued_jobs);
-            println!("{} {:.1}%", t("schedule-stats-success-rate"), s.success_rate);
-            println!("{} {:.1}", t("schedule-stats-avg-exec-ms"), s.avg_execution_time_ms);
+    });
+}
+
+/// The lock on the job table, unless another shell holds it
+fn take_lock(path: &Path) -> Option<File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).ok()?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .ok()?;
+    file.try_lock_exclusive().ok()?;
+    Some(file)
+}
+
+/// Queue a finished job's notice for the prompt and show it on the desktop
+/// when the job asked for that
+fn deliver(notification: &JobNotification) {
+    let text = notice(notification);
+    if notification.channels.iter().any(|c| c == "desktop") {
+        let (title, body) = text.split_once('\n').unwrap_or((&text, ""));
+        show_on_desktop(title, body);
+    }
+    if let Ok(mut notices) = NOTICES.lock() {
+        notices.push(text);
+    }
+}
+
+/// The notice for a finished job: its status, then the start of its output
+fn notice(notification: &JobNotification) -> String {
+    let result = &notification.result;
+    let mut text = format!(
+        "schedule: job {} {}: {}",
+        notification.job_id,
+        status(result),
+        notification.command
+    );
+    // A run that never started has its reason in the status already
+    let errors = if result.error_message.is_some() {
+        ""
+    } else {
+        result.stderr.as_str()
+    };
+    let lines: Vec<&str> = result.stdout.lines().chain(errors.lines()).collect();
+    for line in lines.iter().take(SHOWN_LINES) {
+        text.push('\n');
+        text.push_str(line);
+    }
+    if lines.len() > SHOWN_LINES {
+        let _ = write!(text, "\n... {} more lines", lines.len() - SHOWN_LINES);
+    }
+    text
+}
+
+fn status(result: &JobExecutionResult) -> String {
+    match (&result.error_message, result.exit_code) {
+        (Some(message), _) => format!("failed ({message})"),
+        (None, Some(0)) => "finished".to_string(),
+        (None, Some(code)) => format!("failed with status {code}"),
+        (None, None) => "was killed".to_string(),
+    }
+}
+
+fn show_on_desktop(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let quoted = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = tokio::process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            quoted(body),
+            quoted(title)
+        ));
+        command
+    } else if cfg!(unix) {
+        let mut command = tokio::process::Command::new("notify-send");
+        command.arg("--app-name=nxsh").arg(title).arg(body);
+        command
+    } else {
+        return;
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    tokio::spawn(async move {
+        let _ = command.status().await;
+    });
+}
+
+/// The job table this process edits; other processes see the changes
+/// through the file
+struct Table {
+    runtime: tokio::runtime::Runtime,
+    scheduler: AdvancedJobScheduler,
+}
+
+fn table() -> Result<&'static Table, String> {
+    static TABLE: OnceCell<Table> = OnceCell::new();
+    TABLE.get_or_try_init(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Table {
+            runtime,
+            scheduler: AdvancedJobScheduler::new(config()?),
+        })
+    })
+}
+
+/// The scheduler's settings, with the job table in the configuration directory
+fn config() -> Result<SchedulerConfig, String> {
+    let base = match std::env::var_os("NXSH_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs_next::config_dir()
+            .ok_or("unable to determine config directory")?
+            .join("nexusshell"),
+    };
+    Ok(SchedulerConfig {
+        check_interval_secs: 1,
+        num_workers: 1,
+        state_file: Some(base.join("schedule.json")),
+        ..SchedulerConfig::default()
+    })
+}
+
+/// Which name the command was run by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Schedule,
+    At,
+    Cron,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Schedule => "schedule",
+            Flavor::At => "at",
+            Flavor::Cron => "cron",
+        }
+    }
+
+    /// Whether `-l` under this name shows the job
+    fn lists(self, schedule: &JobSchedule) -> bool {
+        match self {
+            Flavor::Schedule => true,
+            Flavor::At => matches!(schedule, JobSchedule::Once { .. }),
+            Flavor::Cron => matches!(schedule, JobSchedule::Recurring { .. }),
+        }
+    }
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+fn run(flavor: Flavor, args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let name = flavor.name();
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let fail = |outcome: &mut Outcome, status: i32, message: &str| {
+        outcome
+            .stderr
+            .extend(format!("{name}: {message}\n").bytes());
+        outcome.status = outcome.status.max(status);
+    };
+    let request = match Request::parse(flavor, args, Local::now()) {
+        Ok(request) => request,
+        Err(message) => {
+            fail(&mut outcome, 1, &message);
+            return outcome;
         }
-        "-h" | "--help" => {
-            println!("{}", t("schedule-help-title"));
-            println!("{}", t("schedule-help-usage"));
-            println!("{}", t("schedule-help-options-title"));
-            println!("{}", t("schedule-help-option-list-extended"));
-            println!("{}", t("schedule-help-option-delete-extended"));
-            println!("{}", t("schedule-help-option-stats"));
-            println!("{}", t("schedule-help-option-enable"));
-            println!("{}", t("schedule-help-option-disable"));
-            println!("{}", t("schedule-help-option-interval"));
-            println!("{}", t("schedule-help-option-at"));
-            println!("{}", t("schedule-help-option-help"));
+    };
+    let table = match table() {
+        Ok(table) => table,
+        Err(message) => {
+            fail(&mut outcome, BROKEN, &message);
+            return outcome;
         }
-        _ => {
-            if args.len() < 2 {
-                eprintln!("{}", t("schedule-missing-command"));
-                eprintln!("{}", t("schedule-usage-time-cmd"));
-                std::process::exit(1);
+    };
+    let scheduler = &table.scheduler;
+    let done = table.runtime.block_on(async {
+        scheduler.reload().await?;
+        match request {
+            Request::List => {
+                let jobs = scheduler.list_jobs().await;
+                let listed = jobs.iter().filter(|job| flavor.lists(&job.schedule));
+                outcome.stdout = listing(listed.collect()).into_bytes();
             }
-            
-            let time_spec = &args[0];
-            let command = args[1..].join(" ");
-            // Use core scheduler for cron-like expressions; for absolute times fall back to external 'at'
-            if time_spec == "--enable" && args.len() >= 2 {
-                let job_id = &args[1];
-                let (rt, sched) = ensure_scheduler()?;
-                let ok = rt.block_on(async { sched.enable_job(job_id).await })?;
-                if ok { println!("{}", t("schedule-enabled")); } else { eprintln!("{}: {job_id}", t("schedule-job-not-found")); std::process::exit(1); }
-                return Ok(());
-            } else if time_spec == "--disable" && args.len() >= 2 {
-                let job_id = &args[1];
-                let (rt, sched) = ensure_scheduler()?;
-                let ok = rt.block_on(async { sched.disable_job(job_id).await })?;
-                if ok { println!("{}", t("schedule-disabled")); } else { eprintln!("{}: {job_id}", t("schedule-job-not-found")); std::process::exit(1); }
-                return Ok(());
-            } else if time_spec == "--interval" && args.len() >= 3 {
-                let secs: u64 = args[1].parse().map_err(|_| anyhow!("schedule: invalid seconds"))?; // internal error string OK
-                let cmd = args[2..].join(" ");
-                let (rt, sched) = ensure_scheduler()?;
-                let job_id = rt.block_on(async { sched.schedule_interval(cmd.clone(), secs).await })
-                    .map_err(|e| anyhow!("schedule: failed to schedule: {e}"))?;
-                println!("{} {job_id}", t("schedule-scheduled-as"));
-                return Ok(());
-            } else if time_spec == "--at" && args.len() >= 3 {
-                let epoch: u64 = args[1].parse().map_err(|_| anyhow!("schedule: invalid epoch secs"))?; // internal error string OK
-                let cmd = args[2..].join(" ");
-                let (rt, sched) = ensure_scheduler()?;
-                let job_id = rt.block_on(async { sched.schedule_once_epoch(cmd.clone(), epoch).await })
-                    .map_err(|e| anyhow!("schedule: failed to schedule: {e}"))?;
-                println!("{} {job_id}", t("schedule-scheduled-as"));
-                return Ok(());
-            } else if is_cron_like(time_spec) {
-                let (rt, sched) = ensure_scheduler()?;
-                let job_id = rt.block_on(async { sched.schedule_cron(command.clone(), time_spec.clone()).await })
-                    .map_err(|e| anyhow!("schedule: failed to schedule: {e}"))?;
-                println!("{} {job_id}", t("schedule-scheduled-as"));
-                return Ok(());
-            } else {
-                println!("{}", t("schedule-delegating-at"));
-                if let Ok(path) = which("at") {
-                    let status = Command::new(path)
-                        .arg(time_spec)
-                        .arg(command)
-                        .status()
-                        .map_err(|e| anyhow!("schedule: failed to launch 'at': {e}"))?;
-                    std::process::exit(status.code().unwrap_or(1));
+            Request::Add(add) => {
+                let command = match add.command {
+                    Some(command) => command,
+                    None => {
+                        let mut script = String::new();
+                        if let Err(e) = stdin.read_to_string(&mut script) {
+                            fail(&mut outcome, 1, &e.to_string());
+                            return Ok(());
+                        }
+                        if script.trim().is_empty() {
+                            fail(&mut outcome, 1, "no command given");
+                            return Ok(());
+                        }
+                        script.trim_end().to_string()
+                    }
+                };
+                let once = matches!(add.schedule, JobSchedule::Once { .. });
+                let prefix = match add.schedule {
+                    JobSchedule::Once { .. } => "at",
+                    JobSchedule::Interval { .. } => "int",
+                    _ => "cron",
+                };
+                let mut job = scheduler.new_job(prefix, command, add.schedule);
+                job.working_dir = cwd.display().to_string();
+                // Jobs run in the environment of the shell that runs them
+                job.environment.clear();
+                job.timeout_secs = 0;
+                job.retry_config.max_retries = 0;
+                job.notifications.on_success = once || add.notify;
+                job.notifications.on_failure = true;
+                job.notifications.channels = vec!["inline".to_string()];
+                if add.desktop {
+                    job.notifications.channels.push("desktop".to_string());
+                }
+                let when = job.schedule.next_run().map(local_time).unwrap_or_default();
+                let id = scheduler.schedule_job(job).await?;
+                let line = if once {
+                    format!("job {id} at {when}\n")
+                } else {
+                    format!("job {id}, next run at {when}\n")
+                };
+                outcome.stdout = line.into_bytes();
+            }
+            Request::Change(change, ids) => {
+                for id in &ids {
+                    let found = match change {
+                        Change::Remove => scheduler.cancel_job(id).await?,
+                        Change::Enable => scheduler.enable_job(id).await?,
+                        Change::Disable => scheduler.disable_job(id).await?,
+                    };
+                    if !found {
+                        fail(&mut outcome, 1, &format!("{id}: no such job"));
+                    }
                 }
-                return Err(anyhow!("schedule: absolute time scheduling requires 'at' command"));
             }
         }
+        Ok::<_, anyhow::Error>(())
+    });
+    if let Err(e) = done {
+        fail(&mut outcome, BROKEN, &e.to_string());
     }
+    outcome
+}
 
-    Ok(())
-}
-
-fn is_cron_like(spec: &str) -> bool {
-    // Simple heuristic: cron has 5 space-separated fields, allow 6th for seconds in future
-    let parts = spec.split_whitespace().count();
-    parts >= 5 && parts <= 6
-}
-
-// Global scheduler bootstrap (lazily started)
-static RUNTIME: OnceCell<Runtime> = OnceCell::new();
-static SCHEDULER: OnceCell<AdvancedJobScheduler> = OnceCell::new();
-
-fn ensure_scheduler() -> Result<(&'static Runtime, &'static AdvancedJobScheduler)> {
-    let rt = RUNTIME.get_or_try_init(|| Runtime::new().map_err(|e| anyhow!("schedule: runtime init failed: {e}")))?;
-    if SCHEDULER.get().is_none() {
-        let mut sched = AdvancedJobScheduler::new(SchedulerConfig::default());
-        rt.block_on(async { sched.start().await }).map_err(|e| anyhow!("schedule: failed to start scheduler: {e}"))?;
-        SCHEDULER.set(sched).map_err(|_| anyhow!("schedule: failed to set scheduler"))?;
-    }
-    Ok((RUNTIME.get().unwrap(), SCHEDULER.get().unwrap()))
-}
-
-/// Interactive scheduling guide when no arguments are provided
-fn interactive_schedule_guide() -> Result<()> {
-    use std::io::{self, Write};
-
-    println!("╭─────────────────────────────────────────────────────────────╮");
-    println!("━E                   📅 Task Scheduler Guide                   ━E);
-    println!("╰─────────────────────────────────────────────────────────────╯");
-    println!();
-    
-    // Show current status
-    if let Ok((rt, sched)) = ensure_scheduler() {
-        let jobs = rt.block_on(async { sched.list_jobs().await });
-        let stats = rt.block_on(async { sched.get_statistics().await });
-        
-        println!("📊 Current Status:");
-        println!("   • Total jobs: {}", stats.total_jobs);
-        println!("   • Running: {}", stats.running_jobs);
-        println!("   • Queued: {}", stats.queued_jobs);
-        println!("   • Success rate: {:.1}%", stats.success_rate);
-        println!();
-        
-        if !jobs.is_empty() {
-            println!("🕒 Recent Jobs:");
-            for (i, job) in jobs.iter().take(5).enumerate() {
-                let status = if matches!(job.schedule, nxsh_core::advanced_scheduler::JobSchedule::EventBased { .. }) {
-                    "Event-based"
-                } else {
-                    "Scheduled"
-                };
-                println!("   {}. [{}] {} - {}", i + 1, job.id, status, job.command);
+/// What the command line asks for
+#[derive(Debug)]
+enum Request {
+    List,
+    Add(Add),
+    Change(Change, Vec<String>),
+}
+
+/// A change to existing jobs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Remove,
+    Enable,
+    Disable,
+}
+
+/// A job to add
+#[derive(Debug)]
+struct Add {
+    schedule: JobSchedule,
+    /// The command line, or `None` to read it from standard input
+    command: Option<String>,
+    /// `-n`, report successful runs too
+    notify: bool,
+    /// `-D`, show the notices on the desktop as well
+    desktop: bool,
+}
+
+impl Request {
+    fn parse(flavor: Flavor, args: &[String], now: DateTime<Local>) -> Result<Self, String> {
+        let mut args = args;
+        if flavor == Flavor::Schedule {
+            let Some((subcommand, rest)) = args.split_first() else {
+                return Ok(Request::List);
+            };
+            args = rest;
+            let change = match subcommand.as_str() {
+                "list" | "ls" if args.is_empty() => return Ok(Request::List),
+                "list" | "ls" => return Err(format!("unexpected argument '{}'", args[0])),
+                "add" => None,
+                "remove" | "rm" => Some(Change::Remove),
+                "enable" => Some(Change::Enable),
+                "disable" => Some(Change::Disable),
+                other => return Err(format!("unknown subcommand '{other}'")),
+            };
+            if let Some(change) = change {
+                if args.is_empty() {
+                    return Err("missing job ID".to_string());
+                }
+                return Ok(Request::Change(change, args.to_vec()));
             }
-            println!();
         }
-    }
-    
-    println!("🎯 What would you like to do?");
-    println!();
-    println!("   1E⃣  Schedule a one-time task");
-    println!("   2E⃣  Schedule a recurring task (cron-style)");
-    println!("   3E⃣  Schedule an interval-based task");
-    println!("   4E⃣  List all scheduled tasks");
-    println!("   5E⃣  View task statistics");
-    println!("   6E⃣  Delete a task");
-    println!("   7E⃣  Enable/disable a task");
-    println!("   8E⃣  Show help and examples");
-    println!("   0E⃣  Exit");
-    println!();
-    
-    loop {
-        print!("👉 Enter your choice (1-8, 0 to exit): ");
-        io::stdout().flush()?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let choice = input.trim();
-        
-        match choice {
-            "1" => {
-                println!();
-                println!("⏰ Schedule One-Time Task");
-                println!("─────────────────────────");
-                
-                print!("📅 When to run (format: 'YYYY-MM-DD HH:MM' or epoch seconds): ");
-                io::stdout().flush()?;
-                let mut time_input = String::new();
-                io::stdin().read_line(&mut time_input)?;
-                let time_spec = time_input.trim();
-                
-                print!("💻 Command to run: ");
-                io::stdout().flush()?;
-                let mut cmd_input = String::new();
-                io::stdin().read_line(&mut cmd_input)?;
-                let command = cmd_input.trim();
-                
-                if !command.is_empty() {
-                    if let Ok(epoch) = time_spec.parse::<u64>() {
-                        schedule_task_once_epoch(command, epoch)?;
-                    } else {
-                        println!("📝 For absolute time parsing, using external 'at' command...");
-                        schedule_task_external(time_spec, command)?;
-                    }
-                } else {
-                    println!("❁ECommand cannot be empty!");
+
+        let mut notify = false;
+        let mut desktop = false;
+        let mut when = None;
+        let mut iter = args.iter();
+        let mut operands = Vec::new();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
                 }
-            },
-            "2" => {
-                println!();
-                println!("🔄 Schedule Recurring Task (Cron-style)");
-                println!("───────────────────────────────────────");
-                
-                println!("📋 Cron format: minute hour day month day-of-week");
-                println!("   Examples:");
-                println!("   • '0 9 * * *'     - Every day at 9 AM");
-                println!("   • '*/15 * * * *'  - Every 15 minutes");
-                println!("   • '0 0 1 * *'     - First day of each month");
-                println!("   • '0 18 * * 1-5'  - Weekdays at 6 PM");
-                println!();
-                
-                print!("⏰ Cron schedule: ");
-                io::stdout().flush()?;
-                let mut cron_input = String::new();
-                io::stdin().read_line(&mut cron_input)?;
-                let cron_spec = cron_input.trim();
-                
-                print!("💻 Command to run: ");
-                io::stdout().flush()?;
-                let mut cmd_input = String::new();
-                io::stdin().read_line(&mut cmd_input)?;
-                let command = cmd_input.trim();
-                
-                if !command.is_empty() && !cron_spec.is_empty() {
-                    schedule_task_cron(command, cron_spec)?;
-                } else {
-                    println!("❁EBoth schedule and command are required!");
+                "-l" | "--list" if flavor != Flavor::Schedule => {
+                    return match iter.next() {
+                        None => Ok(Request::List),
+                        Some(extra) => Err(format!("unexpected argument '{extra}'")),
+                    };
                 }
-            },
-            "3" => {
-                println!();
-                println!("⚡ Schedule Interval-Based Task");
-                println!("──────────────────────────────");
-                
-                print!("⏱EE Interval in seconds: ");
-                io::stdout().flush()?;
-                let mut interval_input = String::new();
-                io::stdin().read_line(&mut interval_input)?;
-                
-                if let Ok(seconds) = interval_input.trim().parse::<u64>() {
-                    print!("💻 Command to run: ");
-                    io::stdout().flush()?;
-                    let mut cmd_input = String::new();
-                    io::stdin().read_line(&mut cmd_input)?;
-                    let command = cmd_input.trim();
-                    
-                    if !command.is_empty() {
-                        schedule_task_interval(command, seconds)?;
-                    } else {
-                        println!("❁ECommand cannot be empty!");
+                "-r" | "--remove" if flavor != Flavor::Schedule => {
+                    let ids: Vec<String> = iter.cloned().collect();
+                    if ids.is_empty() {
+                        return Err("missing job ID".to_string());
                     }
-                } else {
-                    println!("❁EInvalid interval! Please enter a number of seconds.");
+                    return Ok(Request::Change(Change::Remove, ids));
                 }
-            },
-            "4" => {
-                println!();
-                println!("📋 Scheduled Tasks");
-                println!("─────────────────");
-                list_scheduled_tasks()?;
-            },
-            "5" => {
-                println!();
-                println!("📊 Task Statistics");
-                println!("─────────────────");
-                show_task_statistics()?;
-            },
-            "6" => {
-                println!();
-                println!("🗑EE Delete Task");
-                println!("──────────────");
-                
-                print!("E Task ID to delete: ");
-                io::stdout().flush()?;
-                let mut id_input = String::new();
-                io::stdin().read_line(&mut id_input)?;
-                let task_id = id_input.trim();
-                
-                if !task_id.is_empty() {
-                    delete_task(task_id)?;
-                } else {
-                    println!("❁ETask ID cannot be empty!");
+                "-n" | "--notify" if flavor != Flavor::At => notify = true,
+                "--desktop" => desktop = true,
+                long if flavor == Flavor::Schedule && long.starts_with("--") => {
+                    let (option, inline) = match long.split_once('=') {
+                        Some((option, value)) => (option, Some(value.to_string())),
+                        None => (long, None),
+                    };
+                    if !matches!(option, "--at" | "--in" | "--every" | "--cron") {
+                        return Err(format!("unrecognized option '{long}'"));
+                    }
+                    let value = match inline {
+                        Some(value) => value,
+                        None => iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("option '{option}' requires an argument"))?,
+                    };
+                    if when.is_some() {
+                        return Err("give only one of --at, --in, --every and --cron".to_string());
+                    }
+                    when = Some(match option {
+                        "--at" => {
+                            let words: Vec<String> =
+                                value.split_whitespace().map(str::to_string).collect();
+                            let (at, used) = parse_time(&words, now)?;
+                            if used < words.len() {
+                                return Err(format!("garbled time '{value}'"));
+                            }
+                            once(at, now)?
+                        }
+                        "--in" => once(now + span(duration(&value)?), now)?,
+                        "--every" => {
+                            let interval_secs = duration(&value)?;
+                            if interval_secs == 0 {
+                                return Err("the interval must be longer than 0s".to_string());
+                            }
+                            JobSchedule::Interval {
+                                interval_secs,
+                                next_run: SystemTime::from(now)
+                                    + Duration::from_secs(interval_secs),
+                            }
+                        }
+                        _ => recurring(&value, now)?,
+                    });
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
                 }
-            },
-            "7" => {
-                println!();
-                println!("⚙︁E Enable/Disable Task");
-                println!("─────────────────────");
-                
-                print!("E Task ID: ");
-                io::stdout().flush()?;
-                let mut id_input = String::new();
-                io::stdin().read_line(&mut id_input)?;
-                let task_id = id_input.trim();
-                
-                if !task_id.is_empty() {
-                    print!("🔘 Action (enable/disable): ");
-                    io::stdout().flush()?;
-                    let mut action_input = String::new();
-                    io::stdin().read_line(&mut action_input)?;
-                    let action = action_input.trim().to_lowercase();
-                    
-                    match action.as_str() {
-                        "enable" => enable_task(task_id)?,
-                        "disable" => disable_task(task_id)?,
-                        _ => println!("❁EInvalid action! Use 'enable' or 'disable'."),
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for flag in short[1..].chars() {
+                        match flag {
+                            'n' if flavor != Flavor::At => notify = true,
+                            'D' => desktop = true,
+                            _ => return Err(format!("unknown option -- {flag}")),
+                        }
                     }
-                } else {
-                    println!("❁ETask ID cannot be empty!");
                 }
-            },
-            "8" => {
-                println!();
-                show_help_and_examples();
-            },
-            "0" => {
-                println!("👋 Goodbye!");
-                break;
-            },
-            _ => {
-                println!("❁EInvalid choice! Please enter a number from 0-8.");
-            },
-        }
-        
-        println!();
-        println!("Press Enter to continue...");
-        let mut pause = String::new();
-        io::stdin().read_line(&mut pause)?;
-        println!();
-    }
-    
-    Ok(())
-}
-
-fn schedule_task_once_epoch(command: &str, epoch: u64) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let job_id = rt.block_on(async { sched.schedule_once_epoch(command.to_string(), epoch).await })
-        .map_err(|e| anyhow!("Failed to schedule task: {e}"))?;
-    println!("✁ETask scheduled successfully with ID: {}", job_id);
-    Ok(())
-}
-
-fn schedule_task_external(time_spec: &str, command: &str) -> Result<()> {
-    if let Ok(path) = which("at") {
-        let status = Command::new(path)
-            .arg(time_spec)
-            .arg("-c")
-            .arg(command)
-            .status()
-            .map_err(|e| anyhow!("Failed to launch 'at': {e}"))?;
-        
-        if status.success() {
-            println!("✁ETask scheduled successfully using 'at' command");
-        } else {
-            return Err(anyhow!("'at' command failed"));
+                _ => {
+                    operands.push(arg.clone());
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+            }
         }
-    } else {
-        return Err(anyhow!("External 'at' command not found. Use epoch seconds instead."));
-    }
-    Ok(())
-}
-
-fn schedule_task_cron(command: &str, cron_spec: &str) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let job_id = rt.block_on(async { sched.schedule_cron(command.to_string(), cron_spec.to_string()).await })
-        .map_err(|e| anyhow!("Failed to schedule cron task: {e}"))?;
-    println!("✁ECron task scheduled successfully with ID: {}", job_id);
-    Ok(())
-}
-
-fn schedule_task_interval(command: &str, seconds: u64) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let job_id = rt.block_on(async { sched.schedule_interval(command.to_string(), seconds).await })
-        .map_err(|e| anyhow!("Failed to schedule interval task: {e}"))?;
-    println!("✁EInterval task scheduled successfully with ID: {}", job_id);
-    Ok(())
-}
-
-fn list_scheduled_tasks() -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let jobs = rt.block_on(async { sched.list_jobs().await });
-    
-    if jobs.is_empty() {
-        println!("📭 No scheduled tasks found.");
-        return Ok(());
-    }
-    
-    println!("┌──────────────┬────────────┬─────────────────────┬─────────────────────────────────────────━E);
-    println!("━ETask ID      ━EType       ━ENext Run            ━ECommand                                 ━E);
-    println!("├──────────────┼────────────┼─────────────────────┼─────────────────────────────────────────┤");
-    
-    for job in jobs {
-        let (when, kind) = match job.schedule {
-            nxsh_core::advanced_scheduler::JobSchedule::Once { run_at } => (run_at, "Once"),
-            nxsh_core::advanced_scheduler::JobSchedule::Recurring { next_run, .. } => (next_run, "Cron"),
-            nxsh_core::advanced_scheduler::JobSchedule::Interval { next_run, .. } => (next_run, "Interval"),
-            nxsh_core::advanced_scheduler::JobSchedule::EventBased { .. } => (std::time::SystemTime::UNIX_EPOCH, "Event"),
-        };
-        
-        let time_str = if when == std::time::SystemTime::UNIX_EPOCH {
-            "Event-based".to_string()
-        } else {
-            match when.duration_since(std::time::SystemTime::UNIX_EPOCH) {
-                Ok(d) => {
-                    let timestamp = d.as_secs();
-                    // Simple timestamp to readable format
-                    format!("{}", timestamp)
-                },
-                Err(_) => "Unknown".to_string(),
+
+        let (schedule, words) = match flavor {
+            Flavor::Schedule => {
+                let schedule = when.ok_or("missing --at, --in, --every or --cron")?;
+                (schedule, &operands[..])
+            }
+            Flavor::At => {
+                if operands.is_empty() {
+                    return Err("missing time".to_string());
+                }
+                let (at, used) = parse_time(&operands, now)?;
+                (once(at, now)?, &operands[used..])
+            }
+            Flavor::Cron => {
+                let first = operands.first().ok_or("missing cron expression")?;
+                // Either one word holding the whole expression or five words
+                let (expression, used) = if first.starts_with('@') || first.contains(' ') {
+                    (first.clone(), 1)
+                } else {
+                    let used = operands.len().min(5);
+                    (operands[..used].join(" "), used)
+                };
+                (recurring(&expression, now)?, &operands[used..])
             }
         };
-        
-        let cmd_truncated = if job.command.len() > 39 {
-            format!("{}...", &job.command[..36])
-        } else {
-            job.command.clone()
+        let command = match words {
+            [] if flavor == Flavor::At => None,
+            [] => return Err("missing command".to_string()),
+            [line] => Some(line.clone()),
+            words => Some(words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ")),
         };
-        
-        println!("━E{:<12} ━E{:<10} ━E{:<19} ━E{:<39} ━E, 
-                 job.id, kind, time_str, cmd_truncated);
-    }
-    
-    println!("└──────────────┴────────────┴─────────────────────┴─────────────────────────────────────────━E);
-    Ok(())
-}
-
-fn show_task_statistics() -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let stats = rt.block_on(async { sched.get_statistics().await });
-    
-    println!("📈 Scheduler Statistics:");
-    println!("├─ Total jobs: {}", stats.total_jobs);
-    println!("├─ Running jobs: {}", stats.running_jobs);
-    println!("├─ Queued jobs: {}", stats.queued_jobs);
-    println!("├─ Success rate: {:.1}%", stats.success_rate);
-    println!("└─ Average execution time: {:.1} ms", stats.avg_execution_time_ms);
-    Ok(())
-}
-
-fn delete_task(task_id: &str) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let ok = rt.block_on(async { sched.cancel_job(task_id).await })?;
-    
-    if ok {
-        println!("✁ETask {} deleted successfully", task_id);
-    } else {
-        println!("❁ETask {} not found", task_id);
+        Ok(Request::Add(Add {
+            schedule,
+            command,
+            notify,
+            desktop,
+        }))
     }
-    Ok(())
 }
 
-fn enable_task(task_id: &str) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let ok = rt.block_on(async { sched.enable_job(task_id).await })?;
-    
-    if ok {
-        println!("✁ETask {} enabled successfully", task_id);
-    } else {
-        println!("❁ETask {} not found", task_id);
+/// A one-shot schedule, refusing times that have passed
+fn once(at: DateTime<Local>, now: DateTime<Local>) -> Result<JobSchedule, String> {
+    if at < now - ChronoDuration::minutes(1) {
+        return Err("refusing to schedule a job in the past".to_string());
     }
-    Ok(())
+    Ok(JobSchedule::Once { run_at: at.into() })
 }
 
-fn disable_task(task_id: &str) -> Result<()> {
-    let (rt, sched) = ensure_scheduler()?;
-    let ok = rt.block_on(async { sched.disable_job(task_id).await })?;
-    
-    if ok {
-        println!("✁ETask {} disabled successfully", task_id);
-    } else {
-        println!("❁ETask {} not found", task_id);
-    }
-    Ok(())
-}
-
-fn show_help_and_examples() {
-    println!("🎓 Scheduling Help & Examples");
-    println!("────────────────────────────");
-    println!();
-    
-    println!("📋 Command Line Usage:");
-    println!("   schedule [options] [time] [command]");
-    println!();
-    
-    println!("🎯 Options:");
-    println!("   -l, --list        List all scheduled tasks");
-    println!("   -d, --delete ID   Delete a specific task");
-    println!("   --stats           Show scheduler statistics");
-    println!("   --enable ID       Enable a specific task");
-    println!("   --disable ID      Disable a specific task");
-    println!("   --interval N CMD  Schedule task every N seconds");
-    println!("   --at EPOCH CMD    Schedule task at specific epoch time");
-    println!("   -h, --help        Show this help");
-    println!();
-    
-    println!("📅 Time Formats:");
-    println!("   • Cron: '0 9 * * *' (daily at 9 AM)");
-    println!("   • Cron: '*/15 * * * *' (every 15 minutes)");
-    println!("   • Epoch: Unix timestamp in seconds");
-    println!("   • Date: 'YYYY-MM-DD HH:MM' (requires external 'at')");
-    println!();
-    
-    println!("💡 Examples:");
-    println!("   schedule '0 2 * * *' 'backup.sh'");
-    println!("   schedule --interval 300 'check_health.sh'");
-    println!("   schedule --at 1640995200 'new_year_message.sh'");
-    println!("   schedule -l");
-    println!("   schedule --delete task_001");
-    println!("   schedule --stats");
+/// A cron schedule, refusing expressions that are invalid or never match
+fn recurring(expression: &str, now: DateTime<Local>) -> Result<JobSchedule, String> {
+    advanced_scheduler::validate_cron_expression(expression)
+        .map_err(|e| format!("invalid expression '{expression}': {e}"))?;
+    let next_run = advanced_scheduler::next_cron_run(expression, now.into())
+        .ok_or_else(|| format!("expression '{expression}' never matches a date"))?;
+    Ok(JobSchedule::Recurring {
+        cron_expression: expression.to_string(),
+        next_run,
+        last_run: None,
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Read an `at` TIME from the start of `words`, returning it and the number
+/// of words it took
+fn parse_time(words: &[String], now: DateTime<Local>) -> Result<(DateTime<Local>, usize), String> {
+    let lower: Vec<String> = words.iter().map(|w| w.to_ascii_lowercase()).collect();
+    let garbled = |word: &str| format!("garbled time '{word}'");
+    let first = lower.first().ok_or("missing time")?;
+    let mut used = 1;
+    let clock = match first.as_str() {
+        "now" => None,
+        "noon" => NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => NaiveTime::from_hms_opt(0, 0, 0),
+        word => {
+            let (digits, mut meridiem) = if let Some(digits) = word.strip_suffix("am") {
+                (digits, Some(false))
+            } else if let Some(digits) = word.strip_suffix("pm") {
+                (digits, Some(true))
+            } else {
+                (word, None)
+            };
+            if meridiem.is_none() {
+                meridiem = match lower.get(1).map(String::as_str) {
+                    Some("am") => Some(false),
+                    Some("pm") => Some(true),
+                    _ => None,
+                };
+                used += usize::from(meridiem.is_some());
+            }
+            let (hour, minute) = match digits.split_once(':') {
+                Some((hour, minute)) if minute.len() == 2 => (hour, minute),
+                None if meridiem.is_some() => (digits, "0"),
+                _ => return Err(garbled(&words[0])),
+            };
+            let (Ok(mut hour), Ok(minute)) = (hour.parse::<u32>(), minute.parse::<u32>()) else {
+                return Err(garbled(&words[0]));
+            };
+            if let Some(pm) = meridiem {
+                if !(1..=12).contains(&hour) {
+                    return Err(garbled(&words[0]));
+                }
+                hour = hour % 12 + if pm { 12 } else { 0 };
+            }
+            Some(NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(|| garbled(&words[0]))?)
+        }
+    };
 
-    #[test]
-    fn test_is_cron_like() {
-        // Valid cron expressions
-        assert!(is_cron_like("0 9 * * *"));  // 5 fields
-        assert!(is_cron_like("*/15 * * * *"));  // 5 fields with */
-        assert!(is_cron_like("0 0 1 * * 6"));  // 6 fields (with seconds)
-        assert!(is_cron_like("30 2 * * 1-5"));  // 5 fields with ranges
-        
-        // Invalid expressions
-        assert!(!is_cron_like("tomorrow"));  // 1 field
-        assert!(!is_cron_like("9am"));  // 1 field
-        assert!(!is_cron_like("* * *"));  // 3 fields
-        assert!(!is_cron_like("* * * *"));  // 4 fields
-        assert!(!is_cron_like("* * * * * * *"));  // 7 fields
+    let today = now.date_naive();
+    let date = match lower.get(used).map(String::as_str) {
+        Some("today") => Some(today),
+        Some("tomorrow") => today.succ_opt(),
+        Some(word) => NaiveDate::parse_from_str(word, "%Y-%m-%d").ok(),
+        None => None,
+    };
+    used += usize::from(date.is_some());
+    let local = |date: NaiveDate, time: NaiveTime| {
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(|| format!("{date} {time} does not exist in local time"))
+    };
+    let mut at = match clock {
+        None => local(date.unwrap_or(today), now.time())?,
+        Some(time) => {
+            let at = local(date.unwrap_or(today), time)?;
+            // A clock time that has passed today means tomorrow
+            if date.is_none() && at <= now {
+                local(today.succ_opt().ok_or_else(|| garbled(&words[0]))?, time)?
+            } else {
+                at
+            }
+        }
+    };
+
+    if let Some(increment) = lower.get(used).and_then(|w| w.strip_prefix('+')) {
+        let start = used;
+        used += 1;
+        let mut spec = increment.to_string();
+        if spec.is_empty() {
+            spec = lower
+                .get(used)
+                .cloned()
+                .ok_or_else(|| garbled(&words[start]))?;
+            used += 1;
+        }
+        let (count, unit) = split_count(&spec);
+        let count: u64 = count.parse().map_err(|_| garbled(&words[start]))?;
+        let mut unit = unit.to_string();
+        if unit.is_empty() {
+            unit = lower
+                .get(used)
+                .cloned()
+                .ok_or_else(|| garbled(&words[start]))?;
+            used += 1;
+        }
+        let scale = match unit.as_str() {
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hour" | "hours" => 3600,
+            "d" | "day" | "days" => 86400,
+            "w" | "week" | "weeks" => 604800,
+            _ => return Err(format!("unknown time unit '{unit}'")),
+        };
+        let secs = count
+            .checked_mul(scale)
+            .filter(|secs| *secs <= LONGEST)
+            .ok_or_else(|| garbled(&words[start]))?;
+        at += span(secs);
     }
+    Ok((at, used))
+}
 
-    #[test]
-    fn test_schedule_cli_help() {
-        // Test help option
-        let result = schedule_cli(&["-h".to_string()]);
-        assert!(result.is_ok());
-        
-        let result = schedule_cli(&["--help".to_string()]);
-        assert!(result.is_ok());
+/// `text` split into its leading digits and the rest
+fn split_count(text: &str) -> (&str, &str) {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    text.split_at(digits)
+}
+
+/// `secs`, at most [`LONGEST`], as a chrono duration
+fn span(secs: u64) -> ChronoDuration {
+    ChronoDuration::seconds(secs.min(LONGEST) as i64)
+}
+
+/// Seconds in a DURATION like `90`, `90s`, `15m`, `2h`, `1d` or `1w`
+fn duration(text: &str) -> Result<u64, String> {
+    let (digits, unit) = split_count(text);
+    let count: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration '{text}'"))?;
+    let scale = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 604800,
+        _ => return Err(format!("invalid duration '{text}'")),
+    };
+    count
+        .checked_mul(scale)
+        .filter(|secs| *secs <= LONGEST)
+        .ok_or_else(|| format!("invalid duration '{text}'"))
+}
+
+/// `secs` the way a DURATION is written
+fn format_duration(secs: u64) -> String {
+    match secs {
+        0 => "0s".to_string(),
+        s if s % 604800 == 0 => format!("{}w", s / 604800),
+        s if s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{s}s"),
     }
+}
 
-    #[test]
-    fn test_schedule_cli_stats() {
-        // Test stats option (should work even without scheduler)
-        let result = schedule_cli(&["--stats".to_string()]);
-        // May fail if scheduler can't initialize, but shouldn't panic
-        let _ = result;
+/// `word` quoted for a POSIX shell when it needs it
+fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+fn local_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// The jobs as a table, soonest first
+fn listing(mut jobs: Vec<&advanced_scheduler::ScheduledJob>) -> String {
+    jobs.sort_by_key(|job| (!job.enabled, job.schedule.next_run(), job.id.clone()));
+    let rows: Vec<[String; 4]> = jobs
+        .iter()
+        .map(|job| {
+            let next = match job.schedule.next_run() {
+                Some(time) if job.enabled => local_time(time),
+                Some(_) => "disabled".to_string(),
+                None => "-".to_string(),
+            };
+            let schedule = match &job.schedule {
+                JobSchedule::Once { .. } => "once".to_string(),
+                JobSchedule::Recurring {
+                    cron_expression, ..
+                } => cron_expression.clone(),
+                JobSchedule::Interval { interval_secs, .. } => {
+                    format!("every {}", format_duration(*interval_secs))
+                }
+                JobSchedule::EventBased { .. } => "on event".to_string(),
+            };
+            [
+                job.id.clone(),
+                next,
+                schedule,
+                job.command.replace('\n', "; "),
+            ]
+        })
+        .collect();
+    if rows.is_empty() {
+        return String::new();
+    }
+    let header = ["ID", "NEXT RUN", "SCHEDULE", "COMMAND"].map(str::to_string);
+    let mut widths = [0; 3];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
     }
+    let mut text = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let _ = writeln!(
+            text,
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+    }
+    text
+}
 
-    #[test]
-    fn test_schedule_cli_list() {
-        // Test list option
-        let result = schedule_cli(&["-l".to_string()]);
-        // May fail if scheduler can't initialize, but shouldn't panic
-        let _ = result;
-        
-        let result = schedule_cli(&["--list".to_string()]);
-        let _ = result;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
     }
 
-    #[test]
-    fn test_schedule_cli_invalid_delete() {
-        // Test delete without ID
-        let result = schedule_cli(&["-d".to_string()]);
-        // Should exit with error, but we can't test exit behavior easily
-        let _ = result;
+    /// Monday 2030-03-04 10:20:30 local time
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2030, 3, 4, 10, 20, 30).unwrap()
     }
 
-    #[tokio::test]
-    async fn test_scheduler_initialization() {
-        // Test that scheduler can be initialized
-        let result = ensure_scheduler();
-        // This may fail in test environment, but shouldn't panic
-        let _ = result;
+    fn time(line: &str) -> Result<(String, usize), String> {
+        parse_time(&words(line), now()).map(|(at, used)| (at.format("%F %T").to_string(), used))
     }
 
     #[test]
-    fn test_interactive_functions() {
-        // Test that helper functions don't panic with invalid inputs
-        let result = schedule_task_once_epoch("echo test", 1640995200);
-        let _ = result; // May fail without scheduler, but shouldn't panic
-        
-        let result = schedule_task_cron("echo test", "0 9 * * *");
-        let _ = result; // May fail without scheduler, but shouldn't panic
-        
-        let result = schedule_task_interval("echo test", 300);
-        let _ = result; // May fail without scheduler, but shouldn't panic
+    fn parses_times() {
+        assert_eq!(time("now echo").unwrap(), ("2030-03-04 10:20:30".into(), 1));
+        assert_eq!(
+            time("18:05 make").unwrap(),
+            ("2030-03-04 18:05:00".into(), 1)
+        );
+        assert_eq!(time("09:00").unwrap(), ("2030-03-05 09:00:00".into(), 1));
+        assert_eq!(time("9pm").unwrap(), ("2030-03-04 21:00:00".into(), 1));
+        assert_eq!(
+            time("12:30 am x").unwrap(),
+            ("2030-03-05 00:30:00".into(), 2)
+        );
+        assert_eq!(
+            time("noon tomorrow").unwrap(),
+            ("2030-03-05 12:00:00".into(), 2)
+        );
+        assert_eq!(
+            time("midnight 2030-04-01").unwrap(),
+            ("2030-04-01 00:00:00".into(), 2)
+        );
+        assert_eq!(
+            time("now + 20 minutes tea").unwrap(),
+            ("2030-03-04 10:40:30".into(), 4)
+        );
+        assert_eq!(time("now +2h").unwrap(), ("2030-03-04 12:20:30".into(), 2));
+        assert_eq!(
+            time("10:00 today +1 day").unwrap(),
+            ("2030-03-05 10:00:00".into(), 4)
+        );
+
+        assert_eq!(time("25:00").unwrap_err(), "garbled time '25:00'");
+        assert_eq!(time("13pm").unwrap_err(), "garbled time '13pm'");
+        assert_eq!(time("soon").unwrap_err(), "garbled time 'soon'");
+        assert_eq!(
+            time("now + 3 fortnights").unwrap_err(),
+            "unknown time unit 'fortnights'"
+        );
     }
 
     #[test]
-    fn test_task_management_functions() {
-        // Test task management functions
-        let result = delete_task("nonexistent");
-        let _ = result; // May fail without scheduler, but shouldn't panic
-        
-        let result = enable_task("nonexistent");
-        let _ = result; // May fail without scheduler, but shouldn't panic
-        
-        let result = disable_task("nonexistent");
-        let _ = result; // May fail without scheduler, but shouldn't panic
+    fn parses_durations() {
+        assert_eq!(duration("90"), Ok(90));
+        assert_eq!(duration("15m"), Ok(900));
+        assert_eq!(duration("2h"), Ok(7200));
+        assert_eq!(duration("1w"), Ok(604800));
+        assert!(duration("m").is_err());
+        assert!(duration("5y").is_err());
+        assert_eq!(format_duration(5400), "90m");
+        assert_eq!(format_duration(172800), "2d");
     }
 
     #[test]
-    fn test_help_and_examples() {
-        // Test that help function doesn't panic
-        show_help_and_examples();
+    fn parses_requests() {
+        let add = |flavor, line: &str| match Request::parse(flavor, &words(line), now()) {
+            Ok(Request::Add(add)) => add,
+            other => panic!("{other:?}"),
+        };
+
+        let job = add(Flavor::At, "now + 1 hour echo it's late");
+        assert_eq!(job.command.as_deref(), Some("echo 'it'\\''s' late"));
+        assert!(matches!(job.schedule, JobSchedule::Once { .. }));
+        assert_eq!(add(Flavor::At, "noon").command, None);
+
+        let job = add(Flavor::Cron, "-n 0 4 * * 1-5 backup.sh");
+        assert!(job.notify);
+        assert_eq!(job.command.as_deref(), Some("backup.sh"));
+        let JobSchedule::Recurring {
+            cron_expression, ..
+        } = job.schedule
+        else {
+            panic!("not recurring");
+        };
+        assert_eq!(cron_expression, "0 4 * * 1-5");
+
+        let job = add(Flavor::Schedule, "add -D --every 15m df -h");
+        assert!(job.desktop);
+        assert!(matches!(
+            job.schedule,
+            JobSchedule::Interval {
+                interval_secs: 900,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            Request::parse(Flavor::Schedule, &[], now()),
+            Ok(Request::List)
+        ));
+        assert!(matches!(
+            Request::parse(Flavor::At, &words("-r at_1 at_2"), now()),
+            Ok(Request::Change(Change::Remove, ids)) if ids.len() == 2
+        ));
+
+        let error = |flavor, line: &str| Request::parse(flavor, &words(line), now()).unwrap_err();
+        assert_eq!(
+            error(Flavor::At, "10:00 2020-01-01 x"),
+            "refusing to schedule a job in the past"
+        );
+        assert_eq!(
+            error(Flavor::Cron, "0 4 * *"),
+            "invalid expression '0 4 * *': expected 5 fields, got 4"
+        );
+        assert_eq!(error(Flavor::Cron, "0 4 * * *"), "missing command");
+        assert_eq!(
+            error(Flavor::Cron, "0 4 31 2 * x"),
+            "expression '0 4 31 2 *' never matches a date"
+        );
+        assert_eq!(
+            error(Flavor::Schedule, "add true"),
+            "missing --at, --in, --every or --cron"
+        );
+        assert_eq!(
+            error(Flavor::Schedule, "add --in 1m --every 1m true"),
+            "give only one of --at, --in, --every and --cron"
+        );
+        assert_eq!(
+            error(Flavor::Schedule, "purge"),
+            "unknown subcommand 'purge'"
+        );
+        assert_eq!(error(Flavor::At, "-x now"), "unknown option -- x");
     }
 
     #[test]
-    fn test_list_and_stats_functions() {
-        // Test listing and stats functions
-        let result = list_scheduled_tasks();
-        let _ = result; // May fail without scheduler, but shouldn't panic
-        
-        let result = show_task_statistics();
-        let _ = result; // May fail without scheduler, but shouldn't panic
+    fn quotes_words() {
+        assert_eq!(quote("a-b/c.txt"), "a-b/c.txt");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote(""), "''");
     }
 }
-
@@ -1,41 +1,773 @@
-//! `scp` builtin  Esecure copy utility.
+//! `scp` builtin - copy files to and from other hosts
 //!
-//! This builtin defers to the platform's OpenSSH `scp` implementation to ensure
-//! robust protocol support, cipher negotiation, progress UI, and compatibility
-//! with existing scripts. All command-line arguments are forwarded verbatim.
+//! Syntax:
+//!   scp [-pqr] [-i FILE] [-o OPTION] [-P PORT] SOURCE... TARGET
 //!
-//! If `scp` is not found in the `PATH`, an informative error is returned.
-
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-
-/// Entry point for the `scp` builtin.
-pub fn scp_cli(args: &[String]) -> Result<()> {
-    // Search candidates (Windows may have scp.exe).
-    let candidates = if cfg!(windows) {
-        vec!["scp.exe", "scp"]
-    } else {
-        vec!["scp"]
+//! Copies each SOURCE to TARGET over SSH, where either the SOURCEs or the
+//! TARGET are on another host, written `[USER@]HOST:PATH`; an empty PATH is
+//! the remote user's home directory. The host is reached and logged in to
+//! as `ssh` does, and copies are made with the host's own `scp` in the
+//! classic protocol, so it works with any OpenSSH server.
+//!
+//! Like `cp`, a TARGET that is a directory receives the SOURCEs inside it,
+//! and copying several SOURCEs needs one.
+//!
+//! Options:
+//!   -r          copy directories and what they hold
+//!   -p          keep modification times and permissions
+//!   -q          leave out warnings
+//!   -P PORT     connect to PORT instead of 22
+//!   -i FILE     try the private key in FILE first
+//!   -o OPTION   set an OpenSSH option, as for `ssh`
+//!
+//! The exit status is 0 when everything was copied and 1 otherwise.
+
+use crate::common::ssh::{self as session, quote, Session};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crate::sync_cmd::remote_part;
+use filetime::FileTime;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use russh::client::Msg;
+use russh::{Channel, ChannelMsg};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes of a file sent at a time
+const CHUNK: usize = 32 * 1024;
+
+const USAGE: &str =
+    "usage: scp [-pqr] [-i identity_file] [-o ssh_option] [-P port] source ... target";
+
+/// The `scp` builtin command implementation
+pub struct ScpCommand;
+
+impl Builtin for ScpCommand {
+    fn name(&self) -> &'static str {
+        "scp"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Copy files to and from other hosts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy files, and with -r directories, between this host and another over SSH, \
+         logging in as ssh does."
+    }
+
+    fn usage(&self) -> &'static str {
+        "scp [-pqr] [-i FILE] [-o OPTION] [-P PORT] SOURCE... TARGET"
+    }
+
+    fn help(&self) -> &'static str {
+        "Copy files over SSH. Use 'scp notes.txt alice@example.com:' to copy into your home \
+         there, 'scp -r host:logs .' to fetch a directory or 'scp -P 2222 a b host:/tmp' \
+         for another port."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut stderr = Vec::new();
+        let status = match Options::parse(args) {
+            Ok(options) => run(&options, &ctx.cwd, &mut stderr, &|| ctx.is_timed_out()),
+            Err(message) => {
+                stderr = format!("{message}{USAGE}\n").into_bytes();
+                1
+            }
+        };
+        Ok(ExecutionResult::success(status).with_error(stderr))
+    }
+}
+
+/// Run scp for the legacy dispatcher on the process's standard streams
+pub fn execute(args: &[String], context: &BuiltinContext) -> BuiltinResult<i32> {
+    match Options::parse(args) {
+        Ok(options) => Ok(run(
+            &options,
+            &context.current_dir,
+            &mut io::stderr(),
+            &|| false,
+        )),
+        Err(message) => {
+            eprintln!("{message}{USAGE}");
+            Ok(1)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    ssh: session::Options,
+    recursive: bool,
+    preserve: bool,
+    operands: Vec<String>,
+}
+
+impl Options {
+    /// Parse the arguments, with errors ending in a newline so that the
+    /// usage line can follow them
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            ssh: session::Options::default(),
+            recursive: false,
+            preserve: false,
+            operands: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                options.operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                options.operands.push(arg.clone());
+                continue;
+            };
+            for (at, flag) in flags.char_indices() {
+                match flag {
+                    'r' => options.recursive = true,
+                    'p' => options.preserve = true,
+                    'q' => options.ssh.quiet = true,
+                    'P' | 'i' | 'o' => {
+                        let rest = &flags[at + 1..];
+                        let value = match rest.is_empty() {
+                            false => rest.to_string(),
+                            true => iter.next().cloned().ok_or_else(|| {
+                                format!("option requires an argument -- {flag}\n")
+                            })?,
+                        };
+                        match flag {
+                            'P' => {
+                                options.ssh.port = Some(
+                                    session::parse_port(&value).map_err(|e| format!("{e}\n"))?,
+                                )
+                            }
+                            'i' => options.ssh.identities.push(value.into()),
+                            _ => options
+                                .ssh
+                                .set(&value)
+                                .map_err(|e| format!("command-line line 0: {e}\n"))?,
+                        }
+                        break;
+                    }
+                    _ => return Err(format!("unknown option -- {flag}\n")),
+                }
+            }
+        }
+        if options.operands.len() < 2 {
+            return Err(String::new());
+        }
+        Ok(options)
+    }
+}
+
+fn run(options: &Options, cwd: &Path, errors: &mut dyn Write, stopped: &dyn Fn() -> bool) -> i32 {
+    let (target, sources) = options.operands.split_last().expect("two operands");
+    let copied = match remote_part(target) {
+        Some((host, path)) => match sources.iter().find(|source| remote_part(source).is_some()) {
+            Some(source) => Err(format!(
+                "copying from '{source}' to '{target}' would go between two hosts, \
+                 which is not supported"
+            )),
+            None => upload(options, cwd, sources, host, path, errors, stopped),
+        },
+        None => sources.iter().try_fold(true, |all, source| {
+            let Some((host, path)) = remote_part(source) else {
+                return Err(format!(
+                    "neither '{source}' nor '{target}' is on another host; use cp"
+                ));
+            };
+            let local = cwd.join(target);
+            Ok(download(options, host, path, &local, errors, stopped)? && all)
+        }),
     };
+    match copied {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(message) => {
+            let _ = writeln!(errors, "scp: {message}");
+            1
+        }
+    }
+}
 
-    for bin in candidates {
-        if let Ok(path) = which(bin) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("scp: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
+fn connect(options: &Options, host: &str, errors: &mut dyn Write) -> Result<Session, String> {
+    let target = options.ssh.target(host)?;
+    Session::connect(&target, &options.ssh, errors)
+}
+
+/// The flags the remote `scp` runs with
+fn remote_flags(options: &Options) -> String {
+    let mut flags = String::new();
+    if options.recursive {
+        flags.push_str(" -r");
+    }
+    if options.preserve {
+        flags.push_str(" -p");
+    }
+    flags
+}
+
+/// Send the local `sources` to `path` on `host`, returning whether all of
+/// them arrived
+fn upload(
+    options: &Options,
+    cwd: &Path,
+    sources: &[String],
+    host: &str,
+    path: &str,
+    errors: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+) -> Result<bool, String> {
+    let mut steps = Vec::new();
+    let mut all = true;
+    for source in sources {
+        let local = cwd.join(source);
+        let name = local
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| source.clone());
+        if let Err(message) = plan(&local, name, options.recursive, &mut steps) {
+            let _ = writeln!(errors, "scp: {source}: {message}");
+            all = false;
         }
     }
+    if steps.is_empty() {
+        return Ok(all);
+    }
+    let session = connect(options, host, errors)?;
+    let directory = if sources.len() > 1 { " -d" } else { "" };
+    let path = if path.is_empty() { "." } else { path };
+    let command = format!(
+        "scp -t{}{directory} -- {}",
+        remote_flags(options),
+        quote(path)
+    );
+    let sent = session.block_on(async {
+        let mut wire = Wire::open(&session, &command).await?;
+        let sent = send(&mut wire, &steps, options.preserve, errors, stopped).await;
+        wire.finish(errors).await;
+        sent.map(|sent| sent && wire.status == Some(0))
+    });
+    session.close();
+    Ok(sent? && all)
+}
+
+/// Fetch `path` from `host` into `local`, returning whether all of it
+/// arrived
+fn download(
+    options: &Options,
+    host: &str,
+    path: &str,
+    local: &Path,
+    errors: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+) -> Result<bool, String> {
+    let session = connect(options, host, errors)?;
+    let path = if path.is_empty() { "." } else { path };
+    let command = format!("scp -f{} -- {}", remote_flags(options), quote(path));
+    let received = session.block_on(async {
+        let mut wire = Wire::open(&session, &command).await?;
+        let received = receive(&mut wire, local, options, errors, stopped).await;
+        wire.finish(errors).await;
+        received.map(|received| received && wire.status == Some(0))
+    });
+    session.close();
+    received
+}
 
-    Err(anyhow!("scp: no compatible scp client found in PATH; please install OpenSSH"))
+/// One record to send: a file, or the start or end of a directory
+enum Step {
+    File(PathBuf, String, Metadata),
+    Enter(String, Metadata),
+    Leave,
 }
 
-pub fn execute(args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    match scp_cli(args) {
-        Ok(()) => Ok(0),
-        Err(e) => Err(crate::common::BuiltinError::Other(e.to_string())),
+/// List the records for `local`, sent under `name`
+fn plan(local: &Path, name: String, recursive: bool, steps: &mut Vec<Step>) -> Result<(), String> {
+    let meta = fs::metadata(local).map_err(|e| io_message(&e))?;
+    if meta.is_file() {
+        steps.push(Step::File(local.to_path_buf(), name, meta));
+        return Ok(());
+    }
+    if !meta.is_dir() {
+        return Err("not a regular file".to_string());
     }
+    if !recursive {
+        return Err("is a directory".to_string());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(local)
+        .map_err(|e| io_message(&e))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    entries.sort();
+    steps.push(Step::Enter(name, meta));
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default();
+        let name = name.to_string_lossy().into_owned();
+        plan(&entry, name, recursive, steps)?;
+    }
+    steps.push(Step::Leave);
+    Ok(())
+}
+
+/// Permission bits to send for a file or directory
+fn mode_of(meta: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        meta.permissions().mode() & 0o7777
+    }
+    #[cfg(not(unix))]
+    {
+        if meta.is_dir() {
+            0o755
+        } else {
+            0o644
+        }
+    }
+}
+
+/// The `T` record giving a file's times
+fn times_record(meta: &Metadata) -> String {
+    let mtime = FileTime::from_last_modification_time(meta).unix_seconds();
+    let atime = FileTime::from_last_access_time(meta).unix_seconds();
+    format!("T{mtime} 0 {atime} 0\n")
 }
 
+/// Play the source side of the protocol: each record waits for the remote
+/// `scp` to take it
+async fn send(
+    wire: &mut Wire,
+    steps: &[Step],
+    preserve: bool,
+    errors: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+) -> Result<bool, String> {
+    let mut all = wire.reply(errors).await?;
+    let mut buf = vec![0; CHUNK];
+    for step in steps {
+        if stopped() {
+            return Err("interrupted".to_string());
+        }
+        let meta = match step {
+            Step::File(_, _, meta) | Step::Enter(_, meta) => Some(meta),
+            Step::Leave => None,
+        };
+        if let Some(meta) = meta.filter(|_| preserve) {
+            wire.send(times_record(meta).as_bytes()).await?;
+            all &= wire.reply(errors).await?;
+        }
+        match step {
+            Step::Enter(name, meta) => {
+                wire.send(format!("D{:04o} 0 {name}\n", mode_of(meta)).as_bytes())
+                    .await?;
+                all &= wire.reply(errors).await?;
+            }
+            Step::Leave => {
+                wire.send(b"E\n").await?;
+                all &= wire.reply(errors).await?;
+            }
+            Step::File(path, name, meta) => {
+                let mut file = match File::open(path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        let _ = writeln!(errors, "scp: {}: {}", path.display(), io_message(&e));
+                        all = false;
+                        continue;
+                    }
+                };
+                let header = format!("C{:04o} {} {name}\n", mode_of(meta), meta.len());
+                wire.send(header.as_bytes()).await?;
+                if !wire.reply(errors).await? {
+                    all = false;
+                    continue;
+                }
+                // The header promised this many bytes, so a file that shrank
+                // meanwhile is padded and the copy counts as failed
+                let mut left = meta.len();
+                let mut failure = None;
+                while left > 0 {
+                    let want = buf.len().min(left as usize);
+                    let n = match file.read(&mut buf[..want]) {
+                        Ok(0) => {
+                            failure.get_or_insert_with(|| "file shrank while copying".to_string());
+                            buf[..want].fill(0);
+                            want
+                        }
+                        Ok(n) => n,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            failure.get_or_insert_with(|| io_message(&e));
+                            buf[..want].fill(0);
+                            want
+                        }
+                    };
+                    wire.send(&buf[..n]).await?;
+                    left -= n as u64;
+                }
+                match failure {
+                    Some(message) => {
+                        let _ = writeln!(errors, "scp: {}: {message}", path.display());
+                        wire.send(format!("\x01scp: {name}: {message}\n").as_bytes())
+                            .await?;
+                        all = false;
+                    }
+                    None => wire.send(b"\0").await?,
+                }
+                all &= wire.reply(errors).await?;
+            }
+        }
+    }
+    Ok(all)
+}
+
+/// Parse the rest of a `C` or `D` record: mode, size and name
+fn parse_header(line: &str) -> Option<(u32, u64, &str)> {
+    let mut parts = line.splitn(3, ' ');
+    let mode = u32::from_str_radix(parts.next()?, 8).ok()?;
+    let size = parts.next()?.parse().ok()?;
+    let name = parts.next()?;
+    Some((mode, size, name))
+}
+
+/// Parse the rest of a `T` record into modification and access times
+fn parse_times(line: &str) -> Option<(FileTime, FileTime)> {
+    let numbers: Vec<i64> = line
+        .split(' ')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match numbers[..] {
+        [mtime, mtime_us, atime, atime_us] => Some((
+            FileTime::from_unix_time(mtime, (mtime_us * 1000) as u32),
+            FileTime::from_unix_time(atime, (atime_us * 1000) as u32),
+        )),
+        _ => None,
+    }
+}
+
+/// Play the sink side of the protocol, writing what arrives to `local`
+async fn receive(
+    wire: &mut Wire,
+    local: &Path,
+    options: &Options,
+    errors: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+) -> Result<bool, String> {
+    let into = local.is_dir();
+    let mut all = true;
+    // The directories being received, with the times to give them after
+    let mut dirs: Vec<(PathBuf, Option<(FileTime, FileTime)>)> = Vec::new();
+    let mut times = None;
+    wire.send(b"\0").await?;
+    while let Some(kind) = wire.next().await? {
+        if stopped() {
+            return Err("interrupted".to_string());
+        }
+        let line = wire.line().await?;
+        let place = |name: &str| -> Result<PathBuf, String> {
+            if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                return Err(format!("error: unexpected filename: {name}"));
+            }
+            Ok(match dirs.last() {
+                Some((dir, _)) => dir.join(name),
+                None if into => local.join(name),
+                None => local.to_path_buf(),
+            })
+        };
+        match kind {
+            1 | 2 => {
+                let _ = writeln!(errors, "{line}");
+                all = false;
+                if kind == 2 {
+                    return Ok(false);
+                }
+            }
+            b'T' => {
+                times = Some(parse_times(&line).ok_or("protocol error: mtime.sec not present")?);
+                wire.send(b"\0").await?;
+            }
+            b'D' => {
+                let (mode, _, name) =
+                    parse_header(&line).ok_or("protocol error: bad directory record")?;
+                if !options.recursive {
+                    return Err("received directory without -r".to_string());
+                }
+                let path = place(name)?;
+                if !path.is_dir() {
+                    if let Err(e) = fs::create_dir(&path) {
+                        let message = format!("{}: {}", path.display(), io_message(&e));
+                        wire.send(format!("\x02scp: {message}\n").as_bytes())
+                            .await?;
+                        return Err(message);
+                    }
+                }
+                if options.preserve {
+                    set_mode(&path, mode);
+                }
+                dirs.push((path, times.take()));
+                wire.send(b"\0").await?;
+            }
+            b'E' => {
+                if let Some((path, Some((mtime, atime)))) = dirs.pop() {
+                    let _ = filetime::set_file_times(&path, atime, mtime);
+                }
+                wire.send(b"\0").await?;
+            }
+            b'C' => {
+                let (mode, size, name) =
+                    parse_header(&line).ok_or("protocol error: bad file record")?;
+                let path = place(name)?;
+                let mut file = File::create(&path);
+                wire.send(b"\0").await?;
+                let mut failure = None;
+                match &mut file {
+                    Ok(file) => {
+                        if let Err(e) = wire.take(size, file).await? {
+                            failure = Some(io_message(&e));
+                        }
+                    }
+                    Err(e) => {
+                        failure = Some(io_message(e));
+                        let _ = wire.take(size, &mut io::sink()).await?;
+                    }
+                }
+                // The source ends each file with its own verdict
+                let ended = wire.reply(errors).await?;
+                drop(file);
+                let times = times.take();
+                match failure {
+                    Some(message) => {
+                        let message = format!("{}: {message}", path.display());
+                        let _ = writeln!(errors, "scp: {message}");
+                        wire.send(format!("\x01scp: {message}\n").as_bytes())
+                            .await?;
+                        all = false;
+                    }
+                    None => {
+                        if options.preserve {
+                            set_mode(&path, mode);
+                            if let Some((mtime, atime)) = times {
+                                let _ = filetime::set_file_times(&path, atime, mtime);
+                            }
+                        }
+                        all &= ended;
+                        wire.send(b"\0").await?;
+                    }
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "protocol error: unexpected <{}>",
+                    (kind as char).escape_default()
+                ))
+            }
+        }
+    }
+    Ok(all)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(path, fs::Permissions::from_mode(mode));
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) {}
+
+/// The remote `scp` as a stream of bytes, with what it prints on standard
+/// error and its exit status kept aside
+struct Wire {
+    channel: Channel<Msg>,
+    buffer: Vec<u8>,
+    at: usize,
+    stderr: Vec<u8>,
+    status: Option<u32>,
+    /// The remote side has no more to send
+    ended: bool,
+}
+
+impl Wire {
+    async fn open(session: &Session, command: &str) -> Result<Wire, String> {
+        let channel = session.channel().await?;
+        channel
+            .exec(true, command)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Wire {
+            channel,
+            buffer: Vec::new(),
+            at: 0,
+            stderr: Vec::new(),
+            status: None,
+            ended: false,
+        })
+    }
+
+    /// Wait for the next message, returning false once there are no more
+    async fn pump(&mut self) -> bool {
+        match self.channel.wait().await {
+            Some(ChannelMsg::Data { data }) => {
+                self.buffer.drain(..self.at);
+                self.at = 0;
+                self.buffer.extend_from_slice(&data);
+            }
+            Some(ChannelMsg::ExtendedData { data, .. }) => self.stderr.extend_from_slice(&data),
+            Some(ChannelMsg::ExitStatus { exit_status }) => self.status = Some(exit_status),
+            Some(ChannelMsg::Eof) => self.ended = true,
+            Some(ChannelMsg::Close) | None => {
+                self.ended = true;
+                return false;
+            }
+            Some(_) => {}
+        }
+        true
+    }
+
+    /// The next byte, or `None` at the end
+    async fn next(&mut self) -> Result<Option<u8>, String> {
+        while self.at == self.buffer.len() {
+            if self.ended || !self.pump().await {
+                return Ok(None);
+            }
+        }
+        self.at += 1;
+        Ok(Some(self.buffer[self.at - 1]))
+    }
+
+    /// The rest of a line, without its newline
+    async fn line(&mut self) -> Result<String, String> {
+        let mut line = Vec::new();
+        loop {
+            match self.next().await? {
+                Some(b'\n') => return Ok(String::from_utf8_lossy(&line).into_owned()),
+                Some(byte) => line.push(byte),
+                None => return Err("lost connection".to_string()),
+            }
+        }
+    }
+
+    /// Copy `size` bytes to `out`; an error writing leaves the rest to be
+    /// read and dropped
+    async fn take(&mut self, mut size: u64, out: &mut dyn Write) -> Result<io::Result<()>, String> {
+        let mut written = Ok(());
+        while size > 0 {
+            if self.at == self.buffer.len() {
+                if self.ended || !self.pump().await {
+                    return Err("lost connection".to_string());
+                }
+                continue;
+            }
+            let n = (self.buffer.len() - self.at).min(size.try_into().unwrap_or(usize::MAX));
+            if written.is_ok() {
+                written = out.write_all(&self.buffer[self.at..self.at + n]);
+            }
+            self.at += n;
+            size -= n as u64;
+        }
+        Ok(written)
+    }
+
+    async fn send(&self, bytes: &[u8]) -> Result<(), String> {
+        self.channel.data(bytes).await.map_err(|e| e.to_string())
+    }
+
+    /// Read the other side's answer to a record: true for a plain yes, false
+    /// after printing its complaint about this record, and an error for a
+    /// fatal one
+    async fn reply(&mut self, errors: &mut dyn Write) -> Result<bool, String> {
+        match self.next().await? {
+            Some(0) => Ok(true),
+            Some(1) => {
+                let _ = writeln!(errors, "{}", self.line().await?);
+                Ok(false)
+            }
+            Some(2) => Err(self.line().await?),
+            Some(_) => Err("protocol error: bad reply".to_string()),
+            None => Err("lost connection".to_string()),
+        }
+    }
+
+    /// Close the sending side and wait for the exit status, printing what
+    /// the remote side put on standard error
+    async fn finish(&mut self, errors: &mut dyn Write) {
+        let _ = self.channel.eof().await;
+        while self.pump().await {}
+        let _ = errors.write_all(&self.stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parses_options_anywhere() {
+        let parsed = options(&["-rp", "dir", "-P2222", "host:", "-o", "BatchMode=yes"]).unwrap();
+        assert!(parsed.recursive && parsed.preserve);
+        assert_eq!(parsed.ssh.port, Some(2222));
+        assert!(parsed.ssh.batch);
+        assert_eq!(parsed.operands, vec!["dir", "host:"]);
+
+        assert_eq!(options(&["a"]).unwrap_err(), "");
+        assert_eq!(
+            options(&["-z", "a", "b"]).unwrap_err(),
+            "unknown option -- z\n"
+        );
+        assert_eq!(
+            options(&["a", "b", "-P"]).unwrap_err(),
+            "option requires an argument -- P\n"
+        );
+    }
+
+    #[test]
+    fn reads_records() {
+        assert_eq!(
+            parse_header("0644 12 notes with spaces.txt"),
+            Some((0o644, 12, "notes with spaces.txt"))
+        );
+        assert_eq!(parse_header("0755 0"), None);
+        assert_eq!(parse_header("x 0 a"), None);
+        assert_eq!(
+            parse_times("1000 0 2000 0"),
+            Some((
+                FileTime::from_unix_time(1000, 0),
+                FileTime::from_unix_time(2000, 0)
+            ))
+        );
+        assert_eq!(parse_times("1000 0"), None);
+    }
+
+    #[test]
+    fn plans_directories_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b"), "b").unwrap();
+        fs::write(dir.path().join("a"), "a").unwrap();
+        let mut steps = Vec::new();
+        plan(dir.path(), "top".to_string(), true, &mut steps).unwrap();
+        let shown: Vec<String> = steps
+            .iter()
+            .map(|step| match step {
+                Step::File(_, name, _) => format!("C {name}"),
+                Step::Enter(name, _) => format!("D {name}"),
+                Step::Leave => "E".to_string(),
+            })
+            .collect();
+        assert_eq!(shown, vec!["D top", "C a", "D sub", "C b", "E", "E"]);
+
+        let mut steps = Vec::new();
+        assert_eq!(
+            plan(dir.path(), "top".to_string(), false, &mut steps).unwrap_err(),
+            "is a directory"
+        );
+    }
+}
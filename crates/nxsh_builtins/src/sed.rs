@@ -1,247 +1,1480 @@
-use std::fs;
-use std::io::{BufRead, BufReader, Write};
-use fancy_regex::Regex;
-use crate::common::{BuiltinResult, BuiltinContext};
-
-/// Stream editor for filtering and transforming text
-pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    if args.is_empty() {
-        eprintln!("sed: missing script");
-        return Ok(1);
-    }
-
-    let mut script = String::new();
-    let mut files = Vec::new();
-    let mut in_place = false;
-    let mut quiet = false;
-    let mut extended_regexp = false;
-
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-i" | "--in-place" => in_place = true,
-            "-n" | "--quiet" | "--silent" => quiet = true,
-            "-E" | "-r" | "--regexp-extended" => extended_regexp = true,
-            "-e" | "--expression" => {
-                if i + 1 >= args.len() {
-                    eprintln!("sed: option '{}' requires an argument", args[i]);
-                    return Ok(1);
-                }
-                i += 1;
-                if !script.is_empty() {
-                    script.push('\n');
-                }
-                script.push_str(&args[i]);
-            }
-            "-h" | "--help" => {
-                print_help();
-                return Ok(0);
-            }
-            arg if arg.starts_with('-') => {
-                eprintln!("sed: invalid option '{}'", arg);
-                return Ok(1);
+//! `sed` builtin - stream editor
+//!
+//! Syntax:
+//!   sed [-nEs] [-i[SUFFIX]] SCRIPT [FILE...]
+//!   sed [-nEs] [-i[SUFFIX]] {-e SCRIPT | -f SCRIPT-FILE}... [FILE...]
+//!
+//! Each input line is read into the pattern space, the script runs on it,
+//! and unless `-n` is given the pattern space is printed at the end of the
+//! cycle. Files are one continuous stream unless `-s` or `-i` is given, in
+//! which case line numbers and `$` apply to each file. `-i` writes the
+//! result back to each file, keeping the original as FILE plus SUFFIX, or as
+//! SUFFIX with `*` replaced by the file name.
+//!
+//! Addresses are line numbers, `$`, `/RE/` or `\cREc` (with `I` and `M`
+//! flags), `FIRST~STEP`, and ranges `A,B`, `A,+N`, `A,~N` and `0,/RE/`; a
+//! trailing `!` negates them. The commands are GNU sed's `{ } : b t T = a i
+//! c d D g G h H l n N p P q Q r w s x y z`, with `s` taking the flags `g`,
+//! `p`, `N`, `I`, `M` and `w FILE`, and its replacement `&`, `\1`-`\9`,
+//! `\n`, and the case conversions `\U \L \u \l \E`.
+//!
+//! Regular expressions are translated like `grep`'s, basic by default and
+//! extended with `-E`; backreferences inside a pattern are not supported.
+
+use crate::common::io_message;
+use crate::grep::translate;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use regex::{Captures, Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The `sed` builtin command implementation
+pub struct SedCommand;
+
+impl Builtin for SedCommand {
+    fn name(&self) -> &'static str {
+        "sed"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Stream editor for filtering and transforming text"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a script of editing commands over each line of the input files or standard \
+         input, printing the result or, with -i, writing it back to the files."
+    }
+
+    fn usage(&self) -> &'static str {
+        "sed [-nEs] [-i[SUFFIX]] {SCRIPT | -e SCRIPT... | -f FILE...} [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Stream editor. Use 'sed -i.bak s/old/new/g FILE' to edit a file in place."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args, &ctx.cwd) {
+            Ok(options) => options,
+            Err(message) => return Ok(failure(1, format!("sed: {message}\n"))),
+        };
+        let script = match Script::parse(&options.script, options.extended) {
+            Ok(script) => script,
+            Err(message) => return Ok(failure(1, format!("sed: {message}\n"))),
+        };
+        let mut editor = match Editor::new(&script, options.quiet, &ctx.cwd) {
+            Ok(editor) => editor,
+            Err(message) => return Ok(failure(4, format!("sed: {message}\n"))),
+        };
+
+        let mut stdout = Vec::new();
+        let mut status = 0;
+        if options.in_place.is_some() && options.files.is_empty() {
+            return Ok(failure(1, "sed: no input files\n".to_string()));
+        }
+        let separate = options.separate || options.in_place.is_some();
+        let mut sources = Vec::new();
+        let names: Vec<&str> = if options.files.is_empty() {
+            vec!["-"]
+        } else {
+            options.files.iter().map(String::as_str).collect()
+        };
+        for name in names {
+            if editor.quit.is_some() {
+                break;
             }
-            _ => {
-                if script.is_empty() {
-                    script = args[i].clone();
-                } else {
-                    files.push(&args[i]);
+            let data = if name == "-" {
+                let mut data = Vec::new();
+                ctx.stdin.read_to_end(&mut data).map(|_| data)
+            } else {
+                std::fs::read(ctx.cwd.join(name))
+            };
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => {
+                    editor
+                        .stderr
+                        .push_str(&format!("sed: can't read {name}: {}\n", io_message(&e)));
+                    status = 2;
+                    continue;
+                }
+            };
+            if !separate {
+                sources.push(data);
+                continue;
+            }
+            let mut input = Input::new(&[data]);
+            match &options.in_place {
+                Some(suffix) if name != "-" => {
+                    let mut edited = Vec::new();
+                    let run = editor.run(&mut input, &mut edited);
+                    let path = ctx.cwd.join(name);
+                    if let Err(message) = run.and_then(|_| write_in_place(&path, suffix, &edited)) {
+                        editor.stderr.push_str(&format!("sed: {message}\n"));
+                        status = 4;
+                    }
+                }
+                _ => {
+                    if let Err(message) = editor.run(&mut input, &mut stdout) {
+                        editor.stderr.push_str(&format!("sed: {message}\n"));
+                        status = 4;
+                    }
                 }
             }
         }
-        i += 1;
+        if !separate {
+            let mut input = Input::new(&sources);
+            if let Err(message) = editor.run(&mut input, &mut stdout) {
+                editor.stderr.push_str(&format!("sed: {message}\n"));
+                status = 4;
+            }
+        }
+        if let Some(code) = editor.quit {
+            status = code;
+        }
+        Ok(ExecutionResult::success(status)
+            .with_output(stdout)
+            .with_error(editor.stderr.into_bytes()))
     }
+}
 
-    if script.is_empty() {
-        eprintln!("sed: no script specified");
-        return Ok(1);
-    }
+fn failure(status: i32, message: String) -> ExecutionResult {
+    ExecutionResult::failure(status).with_error(message.into_bytes())
+}
 
-    if files.is_empty() {
-        return process_stdin(&script, quiet, extended_regexp);
+/// Replace a file with its edited contents, keeping a backup when SUFFIX
+/// is not empty
+fn write_in_place(path: &Path, suffix: &str, contents: &[u8]) -> Result<(), String> {
+    let name = path.display();
+    let base = path
+        .file_name()
+        .map(|base| base.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    if !suffix.is_empty() {
+        let backup = if suffix.contains('*') {
+            dir.join(suffix.replace('*', &base))
+        } else {
+            PathBuf::from(format!("{}{suffix}", path.display()))
+        };
+        std::fs::copy(path, &backup).map_err(|e| {
+            format!(
+                "couldn't create backup {}: {}",
+                backup.display(),
+                io_message(&e)
+            )
+        })?;
     }
+    let temp = dir.join(format!(".{base}.sed-{}", std::process::id()));
+    let permissions = std::fs::metadata(path).map(|m| m.permissions());
+    let written = std::fs::write(&temp, contents)
+        .and_then(|_| match permissions {
+            Ok(permissions) => std::fs::set_permissions(&temp, permissions),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    written.map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("couldn't edit {name}: {}", io_message(&e))
+    })
+}
+
+#[derive(Debug)]
+struct Options {
+    script: String,
+    quiet: bool,
+    extended: bool,
+    separate: bool,
+    /// The backup suffix of `-i`, empty for no backup
+    in_place: Option<String>,
+    files: Vec<String>,
+}
 
-    let mut exit_code = 0;
-    for &filename in &files {
-        if let Err(e) = process_file(filename, &script, in_place, quiet, extended_regexp) {
-            eprintln!("sed: {}: {}", filename, e);
-            exit_code = 1;
+impl Options {
+    fn parse(args: &[String], cwd: &Path) -> Result<Self, String> {
+        let mut options = Options {
+            script: String::new(),
+            quiet: false,
+            extended: false,
+            separate: false,
+            in_place: None,
+            files: Vec::new(),
+        };
+        let mut scripts: Vec<String> = Vec::new();
+        let mut operands = Vec::new();
+        let script_file = |file: &str| {
+            std::fs::read_to_string(cwd.join(file))
+                .map(|text| text.strip_suffix('\n').unwrap_or(&text).to_string())
+                .map_err(|e| format!("couldn't open file {file}: {}", io_message(&e)))
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let mut value = || match inline.clone() {
+                    Some(value) => Ok(value),
+                    None => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '--{name}' requires an argument")),
+                };
+                match name {
+                    "expression" => scripts.push(value()?),
+                    "file" => scripts.push(script_file(&value()?)?),
+                    "in-place" => options.in_place = Some(inline.unwrap_or_default()),
+                    "quiet" | "silent" => options.quiet = true,
+                    "regexp-extended" => options.extended = true,
+                    "separate" => options.separate = true,
+                    _ => return Err(format!("unrecognized option '--{name}'")),
+                }
+                continue;
+            }
+            let Some(cluster) = arg.strip_prefix('-').filter(|c| !c.is_empty()) else {
+                operands.push(arg.clone());
+                continue;
+            };
+            for (at, flag) in cluster.char_indices() {
+                let rest = &cluster[at + flag.len_utf8()..];
+                match flag {
+                    'n' => options.quiet = true,
+                    'E' | 'r' => options.extended = true,
+                    's' => options.separate = true,
+                    'i' => {
+                        options.in_place = Some(rest.to_string());
+                        break;
+                    }
+                    'e' | 'f' => {
+                        let value = if rest.is_empty() {
+                            iter.next()
+                                .cloned()
+                                .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?
+                        } else {
+                            rest.to_string()
+                        };
+                        scripts.push(if flag == 'e' {
+                            value
+                        } else {
+                            script_file(&value)?
+                        });
+                        break;
+                    }
+                    other => return Err(format!("invalid option -- '{other}'")),
+                }
+            }
         }
+        let mut operands = operands.into_iter();
+        if scripts.is_empty() {
+            scripts.push(
+                operands
+                    .next()
+                    .ok_or("usage: sed [OPTION]... {SCRIPT} [FILE]...")?,
+            );
+        }
+        options.script = scripts.join("\n");
+        options.files = operands.collect();
+        Ok(options)
     }
+}
+
+/// A compiled sed script
+#[derive(Debug)]
+struct Script {
+    instructions: Vec<Instruction>,
+    /// Set by a `#n` first line, like `-n`
+    quiet: bool,
+}
+
+#[derive(Debug)]
+struct Instruction {
+    selector: Selector,
+    kind: Kind,
+}
 
-    Ok(exit_code)
+/// The addresses in front of a command
+#[derive(Debug, Default)]
+struct Selector {
+    first: Option<Address>,
+    last: Option<RangeEnd>,
+    negate: bool,
 }
 
-fn process_stdin(script: &str, quiet: bool, extended_regexp: bool) -> BuiltinResult<i32> {
-    let stdin = std::io::stdin();
-    let reader = stdin.lock();
-    
-    match process_reader(reader, script, quiet, extended_regexp) {
-        Ok(lines) => {
-            for line in lines {
-                println!("{}", line);
+#[derive(Debug)]
+enum Address {
+    Line(usize),
+    /// `0` of `0,/RE/`, whose range can end on the first line
+    Zero,
+    Last,
+    Step {
+        first: usize,
+        step: usize,
+    },
+    /// `None` for `//`, the last regular expression used
+    Regex(Option<Regex>),
+}
+
+#[derive(Debug)]
+enum RangeEnd {
+    Address(Address),
+    /// `+N`: N more lines
+    Relative(usize),
+    /// `~N`: up to the next line that is a multiple of N
+    Multiple(usize),
+}
+
+#[derive(Debug)]
+enum Kind {
+    /// `{`, holding the index past its `}`
+    BlockStart(usize),
+    BlockEnd,
+    Label,
+    /// `b`, `t` and `T` with their resolved targets
+    Branch(usize),
+    BranchIfSubstituted(usize),
+    BranchUnlessSubstituted(usize),
+    Append(String),
+    Insert(String),
+    Change(String),
+    Delete,
+    DeleteFirst,
+    LineNumber,
+    Get,
+    GetAppend,
+    Hold,
+    HoldAppend,
+    Exchange,
+    List,
+    Next,
+    NextAppend,
+    Print,
+    PrintFirst,
+    Quit(i32),
+    QuitSilent(i32),
+    ReadFile(String),
+    WriteFile(String),
+    Substitute(Box<Substitution>),
+    Transliterate(HashMap<char, char>),
+    Zap,
+}
+
+#[derive(Debug)]
+struct Substitution {
+    /// `None` for an empty pattern, the last regular expression used
+    regex: Option<Regex>,
+    replacement: Vec<Piece>,
+    global: bool,
+    /// Which match to replace, or the first of them with `g`
+    occurrence: usize,
+    print: bool,
+    write: Option<String>,
+}
+
+/// A part of an `s` replacement
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    /// `&` is group 0
+    Group(usize),
+    Case(Case),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+    NextUpper,
+    NextLower,
+    End,
+}
+
+impl Script {
+    fn parse(text: &str, extended: bool) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            extended,
+            label: None,
+        };
+        let quiet = text.starts_with("#n\n") || text == "#n";
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut labels = HashMap::new();
+        let mut jumps = Vec::new();
+        let mut blocks = Vec::new();
+        while let Some(instruction) = parser.instruction()? {
+            let index = instructions.len();
+            match &instruction.kind {
+                Kind::BlockStart(_) => blocks.push(index),
+                Kind::BlockEnd => {
+                    let start = blocks.pop().ok_or_else(|| parser.error("unexpected `}'"))?;
+                    instructions[start].kind = Kind::BlockStart(index + 1);
+                }
+                _ => {}
             }
-            Ok(0)
+            if let Some(label) = parser.label.take() {
+                if matches!(instruction.kind, Kind::Label) {
+                    if labels.insert(label.clone(), index).is_some() {
+                        return Err(format!("duplicate label `{label}'"));
+                    }
+                } else {
+                    jumps.push((index, label));
+                }
+            }
+            instructions.push(instruction);
         }
-        Err(e) => {
-            eprintln!("sed: {}", e);
-            Ok(1)
+        if !blocks.is_empty() {
+            return Err(parser.error("unmatched `{'"));
         }
+        let end = instructions.len();
+        for (index, label) in jumps {
+            let target = if label.is_empty() {
+                end
+            } else {
+                *labels
+                    .get(&label)
+                    .ok_or_else(|| format!("can't find label for jump to `{label}'"))?
+            };
+            match &mut instructions[index].kind {
+                Kind::Branch(to)
+                | Kind::BranchIfSubstituted(to)
+                | Kind::BranchUnlessSubstituted(to) => *to = target,
+                _ => {}
+            }
+        }
+        Ok(Script {
+            instructions,
+            quiet,
+        })
     }
 }
 
-fn process_file(filename: &str, script: &str, in_place: bool, quiet: bool, extended_regexp: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let file = fs::File::open(filename)?;
-    let reader = BufReader::new(file);
-    
-    let processed_lines = process_reader(reader, script, quiet, extended_regexp)?;
-    
-    if in_place {
-        let mut output_file = fs::File::create(filename)?;
-        for line in processed_lines {
-            writeln!(output_file, "{}", line)?;
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    extended: bool,
+    /// The label of the `:`, `b`, `t` or `T` just parsed
+    label: Option<String>,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("-e expression #1, char {}: {message}", self.pos)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
         }
-    } else {
-        for line in processed_lines {
-            println!("{}", line);
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
         }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
     }
-    
-    Ok(())
-}
 
-fn process_reader<R: BufRead>(reader: R, script: &str, quiet: bool, _extended_regexp: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let mut result = Vec::new();
-    
-    // Parse the script - this is a simplified version
-    // Real sed supports much more complex scripting
-    let commands = parse_script(script)?;
-    
-    for line_result in reader.lines() {
-        let line = line_result?;
-        let mut current_line = line;
-        let mut should_print = !quiet;
-        
-        for command in &commands {
-            match apply_command(&current_line, command) {
-                Ok((new_line, print_flag)) => {
-                    current_line = new_line;
-                    if let Some(print) = print_flag {
-                        should_print = print;
+    /// The next instruction, skipping separators and comments
+    fn instruction(&mut self) -> Result<Option<Instruction>, String> {
+        loop {
+            match self.peek() {
+                None => return Ok(None),
+                Some(c) if c.is_whitespace() || c == ';' => self.pos += 1,
+                Some('#') => while !matches!(self.bump(), None | Some('\n')) {},
+                Some(_) => break,
+            }
+        }
+        let mut selector = Selector::default();
+        if let Some(first) = self.address()? {
+            self.skip_blanks();
+            if self.peek() == Some(',') {
+                self.pos += 1;
+                self.skip_blanks();
+                let last = match self.peek() {
+                    Some('+') => {
+                        self.pos += 1;
+                        RangeEnd::Relative(
+                            self.number().ok_or_else(|| self.error("expected number"))?,
+                        )
                     }
+                    Some('~') => {
+                        self.pos += 1;
+                        RangeEnd::Multiple(
+                            self.number().ok_or_else(|| self.error("expected number"))?,
+                        )
+                    }
+                    _ => RangeEnd::Address(
+                        self.address()?
+                            .ok_or_else(|| self.error("unexpected `,'"))?,
+                    ),
+                };
+                selector.last = Some(last);
+            }
+            let zero_ok = matches!(selector.last, Some(RangeEnd::Address(Address::Regex(_))));
+            if matches!(first, Address::Zero) && !zero_ok {
+                return Err(self.error("invalid usage of line address 0"));
+            }
+            selector.first = Some(first);
+        }
+        self.skip_blanks();
+        while self.peek() == Some('!') {
+            self.pos += 1;
+            selector.negate = true;
+            self.skip_blanks();
+        }
+        let command = self.bump().ok_or_else(|| self.error("missing command"))?;
+        let kind = match command {
+            '{' => {
+                let instruction = Instruction {
+                    selector,
+                    kind: Kind::BlockStart(0),
+                };
+                return Ok(Some(instruction));
+            }
+            '}' => {
+                if selector.first.is_some() {
+                    return Err(self.error("} doesn't want any addresses"));
+                }
+                Kind::BlockEnd
+            }
+            ':' => {
+                if selector.first.is_some() {
+                    return Err(self.error(": doesn't want any addresses"));
+                }
+                let label = self.label_name();
+                if label.is_empty() {
+                    return Err(self.error("\":\" lacks a label"));
+                }
+                self.label = Some(label);
+                Kind::Label
+            }
+            'b' | 't' | 'T' => {
+                self.label = Some(self.label_name());
+                match command {
+                    'b' => Kind::Branch(0),
+                    't' => Kind::BranchIfSubstituted(0),
+                    _ => Kind::BranchUnlessSubstituted(0),
+                }
+            }
+            'a' => Kind::Append(self.text()?),
+            'i' => Kind::Insert(self.text()?),
+            'c' => Kind::Change(self.text()?),
+            'q' | 'Q' => {
+                if selector.last.is_some() {
+                    return Err(self.error("command only uses one address"));
+                }
+                self.skip_blanks();
+                let code = self.number().unwrap_or(0) as i32;
+                if command == 'q' {
+                    Kind::Quit(code)
+                } else {
+                    Kind::QuitSilent(code)
+                }
+            }
+            'r' => Kind::ReadFile(self.filename()?),
+            'w' => Kind::WriteFile(self.filename()?),
+            's' => Kind::Substitute(Box::new(self.substitution()?)),
+            'y' => Kind::Transliterate(self.transliteration()?),
+            '=' => Kind::LineNumber,
+            'd' => Kind::Delete,
+            'D' => Kind::DeleteFirst,
+            'g' => Kind::Get,
+            'G' => Kind::GetAppend,
+            'h' => Kind::Hold,
+            'H' => Kind::HoldAppend,
+            'l' => Kind::List,
+            'n' => Kind::Next,
+            'N' => Kind::NextAppend,
+            'p' => Kind::Print,
+            'P' => Kind::PrintFirst,
+            'x' => Kind::Exchange,
+            'z' => Kind::Zap,
+            other => return Err(self.error(&format!("unknown command: `{other}'"))),
+        };
+        self.end_of_command()?;
+        Ok(Some(Instruction { selector, kind }))
+    }
+
+    /// After a command only blanks, then `;`, a newline, `}`, `#` or the end
+    /// of the script may follow
+    fn end_of_command(&mut self) -> Result<(), String> {
+        self.skip_blanks();
+        match self.peek() {
+            None | Some(';') | Some('\n') | Some('}') | Some('#') => Ok(()),
+            Some(_) => Err(self.error("extra characters after command")),
+        }
+    }
+
+    fn address(&mut self) -> Result<Option<Address>, String> {
+        let address = match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let first = self.number().ok_or_else(|| self.error("expected number"))?;
+                if self.peek() == Some('~') {
+                    self.pos += 1;
+                    let step = self.number().ok_or_else(|| self.error("expected number"))?;
+                    Address::Step { first, step }
+                } else if first == 0 {
+                    Address::Zero
+                } else {
+                    Address::Line(first)
+                }
+            }
+            Some('$') => {
+                self.pos += 1;
+                Address::Last
+            }
+            Some('/') | Some('\\') => {
+                let delimiter = match self.bump() {
+                    Some('\\') => self
+                        .bump()
+                        .ok_or_else(|| self.error("unexpected end of script"))?,
+                    _ => '/',
+                };
+                let pattern = self.delimited(delimiter, true)?;
+                let (mut ignore_case, mut multi_line) = (false, false);
+                loop {
+                    match self.peek() {
+                        Some('I') => ignore_case = true,
+                        Some('M') => multi_line = true,
+                        _ => break,
+                    }
+                    self.pos += 1;
+                }
+                Address::Regex(self.regex(&pattern, ignore_case, multi_line)?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(address))
+    }
+
+    /// Read up to an unescaped DELIMITER, turning `\DELIMITER` into the
+    /// delimiter itself; in a regular expression a delimiter inside a
+    /// bracket expression is taken literally
+    fn delimited(&mut self, delimiter: char, regex: bool) -> Result<String, String> {
+        let mut out = String::new();
+        let mut in_bracket = false;
+        loop {
+            let c = self
+                .bump()
+                .ok_or_else(|| self.error("unterminated address regex or `s' command"))?;
+            match c {
+                '\\' => {
+                    let next = self
+                        .bump()
+                        .ok_or_else(|| self.error("unterminated `s' command"))?;
+                    if next == delimiter && !in_bracket {
+                        out.push(delimiter);
+                    } else if next == '\n' {
+                        out.push_str("\\n");
+                    } else {
+                        out.push('\\');
+                        out.push(next);
+                    }
+                }
+                '[' if regex && !in_bracket => {
+                    in_bracket = true;
+                    out.push(c);
+                    if self.peek() == Some('^') {
+                        out.push(self.bump().unwrap_or_default());
+                    }
+                    // A `]` first in the list stands for itself
+                    if self.peek() == Some(']') {
+                        out.push(self.bump().unwrap_or_default());
+                    }
+                }
+                '[' if in_bracket && matches!(self.peek(), Some(':') | Some('.') | Some('=')) => {
+                    let kind = self.bump().unwrap_or_default();
+                    out.push('[');
+                    out.push(kind);
+                    while let Some(c) = self.bump() {
+                        out.push(c);
+                        if c == kind && self.peek() == Some(']') {
+                            out.push(self.bump().unwrap_or_default());
+                            break;
+                        }
+                    }
+                }
+                ']' if in_bracket => {
+                    in_bracket = false;
+                    out.push(c);
+                }
+                c if c == delimiter && !in_bracket => return Ok(out),
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn regex(
+        &self,
+        pattern: &str,
+        ignore_case: bool,
+        multi_line: bool,
+    ) -> Result<Option<Regex>, String> {
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        let source = translate(pattern, self.extended).map_err(|e| self.error(&e))?;
+        RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .multi_line(multi_line)
+            .dot_matches_new_line(true)
+            .build()
+            .map(Some)
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => self.error(&message),
+                other => self.error(&other.to_string()),
+            })
+    }
+
+    fn substitution(&mut self) -> Result<Substitution, String> {
+        let delimiter = self
+            .bump()
+            .filter(|&c| c != '\n' && c != '\\')
+            .ok_or_else(|| self.error("unterminated `s' command"))?;
+        let pattern = self.delimited(delimiter, true)?;
+        let replacement = parse_replacement(&self.delimited(delimiter, false)?);
+        let mut substitution = Substitution {
+            regex: None,
+            replacement,
+            global: false,
+            occurrence: 1,
+            print: false,
+            write: None,
+        };
+        let (mut ignore_case, mut multi_line) = (false, false);
+        loop {
+            match self.peek() {
+                Some('g') => substitution.global = true,
+                Some('p') => substitution.print = true,
+                Some('i') | Some('I') => ignore_case = true,
+                Some('m') | Some('M') => multi_line = true,
+                Some(c) if c.is_ascii_digit() => {
+                    substitution.occurrence = match self.number() {
+                        Some(0) | None => {
+                            return Err(self.error("number option to `s' command may not be zero"))
+                        }
+                        Some(n) => n,
+                    };
+                    continue;
+                }
+                Some('w') => {
+                    self.pos += 1;
+                    substitution.write = Some(self.filename()?);
+                    break;
                 }
-                Err(e) => return Err(e),
+                Some('e') => return Err(self.error("the `e' flag is not supported")),
+                _ => break,
             }
+            self.pos += 1;
+        }
+        substitution.regex = self.regex(&pattern, ignore_case, multi_line)?;
+        Ok(substitution)
+    }
+
+    fn transliteration(&mut self) -> Result<HashMap<char, char>, String> {
+        let delimiter = self
+            .bump()
+            .filter(|&c| c != '\n' && c != '\\')
+            .ok_or_else(|| self.error("unterminated `y' command"))?;
+        let unescape = |text: String| {
+            let mut out = Vec::new();
+            let mut chars = text.chars();
+            while let Some(c) = chars.next() {
+                out.push(match (c, c == '\\') {
+                    (_, true) => match chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some(other) => other,
+                        None => '\\',
+                    },
+                    (c, false) => c,
+                });
+            }
+            out
+        };
+        let from = unescape(self.delimited(delimiter, false)?);
+        let to = unescape(self.delimited(delimiter, false)?);
+        if from.len() != to.len() {
+            return Err(self.error("strings for `y' command are different lengths"));
+        }
+        Ok(from.into_iter().zip(to).collect())
+    }
+
+    /// The text of `a`, `i` or `c`: either `a\` and the following lines, or
+    /// GNU's one-line `a TEXT`. A line ending in a backslash continues the
+    /// text on the next line.
+    fn text(&mut self) -> Result<String, String> {
+        self.skip_blanks();
+        if self.peek() == Some('\\') {
+            self.pos += 1;
+            self.skip_blanks();
+            if self.peek() == Some('\n') {
+                self.pos += 1;
+            }
+        }
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.pos += 1;
+            if c != '\\' {
+                text.push(c);
+            } else if let Some(next) = self.bump() {
+                text.push(next);
+            }
+        }
+        Ok(text)
+    }
+
+    /// The file name of `r`, `w` or the `w` flag, to the end of the line
+    fn filename(&mut self) -> Result<String, String> {
+        self.skip_blanks();
+        let start = self.pos;
+        while !matches!(self.peek(), None | Some('\n')) {
+            self.pos += 1;
         }
-        
-        if should_print {
-            result.push(current_line);
+        let name: String = self.chars[start..self.pos].iter().collect();
+        if name.is_empty() {
+            return Err(self.error("missing filename in r/R/w/W commands"));
         }
+        Ok(name)
+    }
+
+    fn label_name(&mut self) -> String {
+        self.skip_blanks();
+        let start = self.pos;
+        while !matches!(self.peek(), None | Some('\n') | Some(';') | Some('}')) {
+            self.pos += 1;
+        }
+        let label: String = self.chars[start..self.pos].iter().collect();
+        label.trim_end().to_string()
     }
-    
-    Ok(result)
 }
 
-#[derive(Debug, Clone)]
-enum SedCommand {
-    Substitute { pattern: String, replacement: String, global: bool },
-    Delete,
-    Print,
-    Next,
+/// Split an `s` replacement into text, group references and case changes
+fn parse_replacement(raw: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = raw.chars();
+    let push = |pieces: &mut Vec<Piece>, text: &mut String, piece: Piece| {
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(text)));
+        }
+        pieces.push(piece);
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => push(&mut pieces, &mut text, Piece::Group(0)),
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => push(
+                    &mut pieces,
+                    &mut text,
+                    Piece::Group(d.to_digit(10).unwrap_or(0) as usize),
+                ),
+                Some('U') => push(&mut pieces, &mut text, Piece::Case(Case::Upper)),
+                Some('L') => push(&mut pieces, &mut text, Piece::Case(Case::Lower)),
+                Some('u') => push(&mut pieces, &mut text, Piece::Case(Case::NextUpper)),
+                Some('l') => push(&mut pieces, &mut text, Piece::Case(Case::NextLower)),
+                Some('E') => push(&mut pieces, &mut text, Piece::Case(Case::End)),
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
 }
 
-fn parse_script(script: &str) -> Result<Vec<SedCommand>, Box<dyn std::error::Error>> {
-    let mut commands = Vec::new();
-    
-    for command_str in script.split('\n') {
-        let cmd = command_str.trim();
-        if cmd.is_empty() {
-            continue;
-        }
-        
-        if cmd.starts_with('s') {
-            // Parse substitute command: s/pattern/replacement/flags
-            let parts: Vec<&str> = cmd.splitn(4, '/').collect();
-            if parts.len() < 3 {
-                return Err("Invalid substitute command syntax".into());
-            }
-            
-            let pattern = parts[1].to_string();
-            let replacement = parts[2].to_string();
-            let flags = if parts.len() > 3 { parts[3] } else { "" };
-            let global = flags.contains('g');
-            
-            commands.push(SedCommand::Substitute { pattern, replacement, global });
-        } else if cmd == "d" {
-            commands.push(SedCommand::Delete);
-        } else if cmd == "p" {
-            commands.push(SedCommand::Print);
-        } else if cmd == "n" {
-            commands.push(SedCommand::Next);
-        } else {
-            // Simple substitute without delimiters (GNU sed extension)
-            if cmd.contains('=') {
-                let parts: Vec<&str> = cmd.splitn(2, '=').collect();
-                if parts.len() == 2 {
-                    commands.push(SedCommand::Substitute {
-                        pattern: parts[0].to_string(),
-                        replacement: parts[1].to_string(),
-                        global: false,
-                    });
-                }
+/// Expand a replacement for one match
+fn expand(pieces: &[Piece], captures: &Captures, out: &mut String) {
+    let mut mode = None;
+    let mut next = None;
+    for piece in pieces {
+        let text = match piece {
+            Piece::Text(text) => text.as_str(),
+            Piece::Group(n) => captures.get(*n).map_or("", |m| m.as_str()),
+            Piece::Case(case @ (Case::Upper | Case::Lower)) => {
+                mode = Some(*case);
+                continue;
+            }
+            Piece::Case(Case::End) => {
+                mode = None;
+                next = None;
+                continue;
+            }
+            Piece::Case(case) => {
+                next = Some(*case);
+                continue;
+            }
+        };
+        for c in text.chars() {
+            match next.take().or(mode) {
+                Some(Case::Upper) | Some(Case::NextUpper) => out.extend(c.to_uppercase()),
+                Some(Case::Lower) | Some(Case::NextLower) => out.extend(c.to_lowercase()),
+                _ => out.push(c),
+            }
+        }
+    }
+}
+
+/// The lines of the input, and where the editor is in them
+struct Input {
+    /// Each line with whether a newline ended it
+    lines: Vec<(String, bool)>,
+    next: usize,
+}
+
+impl Input {
+    /// The lines of the sources as one stream; only the very last line can
+    /// lack a newline
+    fn new(sources: &[Vec<u8>]) -> Self {
+        let mut lines = Vec::new();
+        for data in sources {
+            let text = String::from_utf8_lossy(data);
+            let mut parts: Vec<&str> = text.split('\n').collect();
+            if text.ends_with('\n') || text.is_empty() {
+                parts.pop();
             }
+            lines.extend(parts.into_iter().map(|line| (line.to_string(), true)));
+        }
+        let last_source = sources.iter().rev().find(|data| !data.is_empty());
+        if let (Some(last), Some(data)) = (lines.last_mut(), last_source) {
+            last.1 = data.ends_with(b"\n");
         }
+        Input { lines, next: 0 }
     }
-    
-    if commands.is_empty() {
-        return Err("No valid commands found in script".into());
+
+    fn read(&mut self) -> Option<(String, bool)> {
+        let line = self.lines.get(self.next).cloned()?;
+        self.next += 1;
+        Some(line)
+    }
+
+    /// The number of the line read last
+    fn line_number(&self) -> usize {
+        self.next
+    }
+
+    fn is_last(&self) -> bool {
+        self.next >= self.lines.len()
     }
-    
-    Ok(commands)
 }
 
-fn apply_command(line: &str, command: &SedCommand) -> Result<(String, Option<bool>), Box<dyn std::error::Error>> {
-    match command {
-        SedCommand::Substitute { pattern, replacement, global } => {
-            let regex = Regex::new(pattern)?;
-            let result = if *global {
-                regex.replace_all(line, replacement.as_str()).to_string()
-            } else {
-                regex.replace(line, replacement.as_str()).to_string()
+/// Where a range of lines stands
+#[derive(Debug, Default, Clone, Copy)]
+struct RangeState {
+    active: bool,
+    /// The line a numeric end stops at
+    until: Option<usize>,
+    /// Whether a `0,/RE/` range has started
+    zero_used: bool,
+}
+
+/// The state of a running script, kept across files
+struct Editor<'a> {
+    script: &'a Script,
+    quiet: bool,
+    hold: String,
+    last_regex: Option<Regex>,
+    ranges: Vec<RangeState>,
+    /// Whether the selector just tested ended a range, or had none
+    range_last: bool,
+    substituted: bool,
+    writers: HashMap<String, std::fs::File>,
+    cwd: PathBuf,
+    quit: Option<i32>,
+    stderr: String,
+}
+
+/// What the end of a cycle does
+enum CycleEnd {
+    /// Print the pattern space unless `-n` is in effect
+    AutoPrint,
+    /// Start the next cycle without printing
+    Discard,
+    /// `D`: start again with what is left of the pattern space
+    Restart(String),
+}
+
+impl<'a> Editor<'a> {
+    /// An editor for SCRIPT, with every `w` file created empty
+    fn new(script: &'a Script, quiet: bool, cwd: &Path) -> Result<Self, String> {
+        let mut writers = HashMap::new();
+        for instruction in &script.instructions {
+            let path = match &instruction.kind {
+                Kind::WriteFile(path) => path,
+                Kind::Substitute(s) => match &s.write {
+                    Some(path) => path,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if path == "/dev/stdout" || path == "/dev/stderr" || writers.contains_key(path) {
+                continue;
+            }
+            let file = std::fs::File::create(cwd.join(path))
+                .map_err(|e| format!("couldn't open file {path}: {}", io_message(&e)))?;
+            writers.insert(path.clone(), file);
+        }
+        Ok(Editor {
+            script,
+            quiet: quiet || script.quiet,
+            hold: String::new(),
+            last_regex: None,
+            ranges: vec![RangeState::default(); script.instructions.len()],
+            range_last: true,
+            substituted: false,
+            writers,
+            cwd: cwd.to_path_buf(),
+            quit: None,
+            stderr: String::new(),
+        })
+    }
+
+    /// Run the script over every line of INPUT
+    fn run(&mut self, input: &mut Input, out: &mut Vec<u8>) -> Result<(), String> {
+        let script = self.script;
+        self.ranges.fill(RangeState::default());
+        let mut carried = None;
+        while self.quit.is_none() {
+            let (mut space, mut newline) = match carried.take() {
+                Some(space) => (space, true),
+                None => match input.read() {
+                    Some(line) => line,
+                    None => break,
+                },
             };
-            Ok((result, None))
-        }
-        SedCommand::Delete => {
-            Ok((line.to_string(), Some(false)))
-        }
-        SedCommand::Print => {
-            Ok((line.to_string(), Some(true)))
-        }
-        SedCommand::Next => {
-            Ok((line.to_string(), None))
-        }
-    }
-}
-
-fn print_help() {
-    println!("Usage: sed [OPTION]... SCRIPT [FILE]...");
-    println!("Stream editor for filtering and transforming text.");
-    println!();
-    println!("Options:");
-    println!("  -e, --expression=SCRIPT  add the script to the commands to be executed");
-    println!("  -i, --in-place          edit files in place");
-    println!("  -n, --quiet, --silent   suppress automatic printing of pattern space");
-    println!("  -E, -r, --regexp-extended  use extended regular expressions");
-    println!("  -h, --help              display this help and exit");
-    println!();
-    println!("SCRIPT is a sequence of sed commands. Common commands:");
-    println!("  s/pattern/replacement/  substitute pattern with replacement");
-    println!("  s/pattern/replacement/g substitute all occurrences");
-    println!("  d                       delete pattern space");
-    println!("  p                       print pattern space");
-    println!();
-    println!("Examples:");
-    println!("  sed 's/old/new/g' file.txt      Replace all 'old' with 'new'");
-    println!("  sed -i 's/foo/bar/' file.txt     Replace first 'foo' with 'bar' in-place");
-    println!("  sed -n '1,5p' file.txt           Print lines 1-5 only");
+            self.substituted = false;
+            let mut appended: Vec<Appended> = Vec::new();
+            let mut pc = 0;
+            let mut end = CycleEnd::AutoPrint;
+            while let Some(instruction) = script.instructions.get(pc) {
+                if !self.selected(pc, input, &space)? {
+                    pc = match instruction.kind {
+                        Kind::BlockStart(past) => past,
+                        _ => pc + 1,
+                    };
+                    continue;
+                }
+                pc += 1;
+                match &instruction.kind {
+                    Kind::BlockStart(_) | Kind::BlockEnd | Kind::Label => {}
+                    Kind::Branch(target) => pc = *target,
+                    Kind::BranchIfSubstituted(target) => {
+                        if std::mem::take(&mut self.substituted) {
+                            pc = *target;
+                        }
+                    }
+                    Kind::BranchUnlessSubstituted(target) => {
+                        if !std::mem::take(&mut self.substituted) {
+                            pc = *target;
+                        }
+                    }
+                    Kind::Append(text) => appended.push(Appended::Text(text)),
+                    Kind::Insert(text) => line_out(out, text),
+                    Kind::Change(text) => {
+                        if self.range_last || instruction.selector.negate {
+                            line_out(out, text);
+                        }
+                        end = CycleEnd::Discard;
+                        break;
+                    }
+                    Kind::Delete => {
+                        end = CycleEnd::Discard;
+                        break;
+                    }
+                    Kind::DeleteFirst => {
+                        end = match space.split_once('\n') {
+                            Some((_, rest)) => CycleEnd::Restart(rest.to_string()),
+                            None => CycleEnd::Discard,
+                        };
+                        break;
+                    }
+                    Kind::LineNumber => line_out(out, &input.line_number().to_string()),
+                    Kind::Get => space = self.hold.clone(),
+                    Kind::GetAppend => {
+                        space.push('\n');
+                        space.push_str(&self.hold);
+                    }
+                    Kind::Hold => self.hold = space.clone(),
+                    Kind::HoldAppend => {
+                        self.hold.push('\n');
+                        self.hold.push_str(&space);
+                    }
+                    Kind::Exchange => std::mem::swap(&mut space, &mut self.hold),
+                    Kind::List => out.extend(list(&space).as_bytes()),
+                    Kind::Next => {
+                        if input.is_last() {
+                            break;
+                        }
+                        if !self.quiet {
+                            line_out(out, &space);
+                        }
+                        self.flush(&mut appended, out);
+                        if let Some((line, ends)) = input.read() {
+                            space = line;
+                            newline = ends;
+                        }
+                    }
+                    Kind::NextAppend => match input.read() {
+                        Some((line, ends)) => {
+                            space.push('\n');
+                            space.push_str(&line);
+                            newline = ends;
+                        }
+                        None => break,
+                    },
+                    Kind::Print => line_out(out, &space),
+                    Kind::PrintFirst => line_out(out, space.split('\n').next().unwrap_or("")),
+                    Kind::Quit(code) => {
+                        self.quit = Some(*code);
+                        break;
+                    }
+                    Kind::QuitSilent(code) => {
+                        self.quit = Some(*code);
+                        end = CycleEnd::Discard;
+                        break;
+                    }
+                    Kind::ReadFile(path) => appended.push(Appended::File(path)),
+                    Kind::WriteFile(path) => self.write(path, &space, out)?,
+                    Kind::Substitute(substitution) => {
+                        if let Some(result) = self.substitute(substitution, &space)? {
+                            space = result;
+                            self.substituted = true;
+                            if substitution.print {
+                                line_out(out, &space);
+                            }
+                            if let Some(path) = &substitution.write {
+                                self.write(path, &space, out)?;
+                            }
+                        }
+                    }
+                    Kind::Transliterate(map) => {
+                        space = space
+                            .chars()
+                            .map(|c| map.get(&c).copied().unwrap_or(c))
+                            .collect();
+                    }
+                    Kind::Zap => space.clear(),
+                }
+            }
+            match end {
+                CycleEnd::AutoPrint if !self.quiet => {
+                    out.extend(space.as_bytes());
+                    if newline {
+                        out.push(b'\n');
+                    }
+                }
+                CycleEnd::Restart(rest) => carried = Some(rest),
+                _ => {}
+            }
+            self.flush(&mut appended, out);
+        }
+        Ok(())
+    }
+
+    /// Whether the instruction at PC applies to the current line
+    fn selected(&mut self, pc: usize, input: &Input, space: &str) -> Result<bool, String> {
+        let script = self.script;
+        let selector = &script.instructions[pc].selector;
+        let line = input.line_number();
+        let is_last = input.is_last();
+        self.range_last = true;
+        let hit = match (&selector.first, &selector.last) {
+            (None, _) => true,
+            (Some(first), None) => self.matches(first, line, is_last, space)?,
+            (Some(first), Some(last)) => {
+                let mut state = self.ranges[pc];
+                if !state.active && matches!(first, Address::Zero) && !state.zero_used {
+                    state.active = true;
+                    state.zero_used = true;
+                    // The range is open before the first line, so its end is
+                    // tested there
+                } else if !state.active {
+                    if !self.matches(first, line, is_last, space)? {
+                        return Ok(selector.negate);
+                    }
+                    let single = match last {
+                        RangeEnd::Address(Address::Line(end)) => {
+                            state.until = Some(*end);
+                            *end <= line
+                        }
+                        RangeEnd::Relative(n) => {
+                            state.until = Some(line + n);
+                            *n == 0
+                        }
+                        RangeEnd::Multiple(n) => {
+                            state.until = (*n > 0).then(|| (line / n + 1) * n);
+                            *n == 0 || line % n == 0
+                        }
+                        RangeEnd::Address(Address::Last) => is_last,
+                        RangeEnd::Address(_) => false,
+                    };
+                    state.active = !single;
+                    self.range_last = single;
+                    self.ranges[pc] = state;
+                    return Ok(!selector.negate);
+                }
+                let ended = match (state.until, last) {
+                    (Some(until), _) => line >= until,
+                    (None, RangeEnd::Address(end)) => self.matches(end, line, is_last, space)?,
+                    (None, _) => true,
+                };
+                state.active = !ended;
+                self.range_last = ended;
+                self.ranges[pc] = state;
+                true
+            }
+        };
+        Ok(hit != selector.negate)
+    }
+
+    fn matches(
+        &mut self,
+        address: &Address,
+        line: usize,
+        is_last: bool,
+        space: &str,
+    ) -> Result<bool, String> {
+        Ok(match address {
+            Address::Line(n) => line == *n,
+            Address::Zero => false,
+            Address::Last => is_last,
+            Address::Step { first, step: 0 } => line == *first,
+            Address::Step { first, step } => line >= *first && (line - first) % step == 0,
+            Address::Regex(regex) => self.regex(regex)?.is_match(space),
+        })
+    }
+
+    /// REGEX, or the last one used for `//`, remembered for the next `//`
+    fn regex(&mut self, regex: &Option<Regex>) -> Result<Regex, String> {
+        if let Some(regex) = regex {
+            self.last_regex = Some(regex.clone());
+        }
+        self.last_regex
+            .clone()
+            .ok_or_else(|| "no previous regular expression".to_string())
+    }
+
+    /// The pattern space after SUBSTITUTION, or `None` when nothing matched
+    fn substitute(
+        &mut self,
+        substitution: &Substitution,
+        space: &str,
+    ) -> Result<Option<String>, String> {
+        let regex = self.regex(&substitution.regex)?;
+        let mut result = String::new();
+        let mut copied = 0;
+        let mut count = 0;
+        let mut previous_end = None;
+        for captures in regex.captures_iter(space) {
+            let Some(found) = captures.get(0) else {
+                continue;
+            };
+            // Like GNU sed, an empty match right after a match is skipped
+            if found.is_empty() && previous_end == Some(found.start()) {
+                continue;
+            }
+            previous_end = Some(found.end());
+            count += 1;
+            if count < substitution.occurrence {
+                continue;
+            }
+            result.push_str(&space[copied..found.start()]);
+            expand(&substitution.replacement, &captures, &mut result);
+            copied = found.end();
+            if !substitution.global {
+                break;
+            }
+        }
+        if count < substitution.occurrence {
+            return Ok(None);
+        }
+        result.push_str(&space[copied..]);
+        Ok(Some(result))
+    }
+
+    fn write(&mut self, path: &str, text: &str, out: &mut Vec<u8>) -> Result<(), String> {
+        match path {
+            "/dev/stdout" => line_out(out, text),
+            "/dev/stderr" => {
+                self.stderr.push_str(text);
+                self.stderr.push('\n');
+            }
+            _ => {
+                if let Some(file) = self.writers.get_mut(path) {
+                    writeln!(file, "{text}")
+                        .map_err(|e| format!("couldn't write to {path}: {}", io_message(&e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Output the text of `a` and the files of `r` queued in this cycle;
+    /// a file that cannot be read is left out silently
+    fn flush(&self, appended: &mut Vec<Appended>, out: &mut Vec<u8>) {
+        for item in appended.drain(..) {
+            match item {
+                Appended::Text(text) => line_out(out, text),
+                Appended::File(path) => {
+                    if let Ok(data) = std::fs::read(self.cwd.join(path)) {
+                        out.extend(&data);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Output queued for the end of a cycle
+enum Appended<'s> {
+    Text(&'s str),
+    File(&'s str),
+}
+
+fn line_out(out: &mut Vec<u8>, text: &str) {
+    out.extend(text.as_bytes());
+    out.push(b'\n');
+}
+
+/// The pattern space the way `l` shows it: escapes for unprintable bytes,
+/// lines folded at 70 columns and a `$` at the end
+fn list(space: &str) -> String {
+    const WIDTH: usize = 70;
+    let mut out = String::new();
+    let mut column = 0;
+    for &byte in space.as_bytes() {
+        let shown = match byte {
+            b'\\' => "\\\\".to_string(),
+            0x07 => "\\a".to_string(),
+            0x08 => "\\b".to_string(),
+            0x0c => "\\f".to_string(),
+            b'\n' => "\\n".to_string(),
+            b'\r' => "\\r".to_string(),
+            b'\t' => "\\t".to_string(),
+            0x0b => "\\v".to_string(),
+            0x20..=0x7e => (byte as char).to_string(),
+            _ => format!("\\{byte:03o}"),
+        };
+        if column + shown.len() > WIDTH - 1 {
+            out.push_str("\\\n");
+            column = 0;
+        }
+        column += shown.len();
+        out.push_str(&shown);
+    }
+    out.push_str("$\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sed(script: &str, input: &str) -> String {
+        sed_with(script, input, false)
+    }
+
+    fn sed_with(script: &str, input: &str, quiet: bool) -> String {
+        let script = Script::parse(script, false).unwrap();
+        let mut editor = Editor::new(&script, quiet, Path::new(".")).unwrap();
+        let mut input = Input::new(&[input.as_bytes().to_vec()]);
+        let mut out = Vec::new();
+        editor.run(&mut input, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn substitutes_with_flags_and_groups() {
+        assert_eq!(sed("s/o/0/", "foo\n"), "f0o\n");
+        assert_eq!(sed("s/o/0/g", "foo\n"), "f00\n");
+        assert_eq!(sed("s/o/0/2", "fooo\n"), "fo0o\n");
+        assert_eq!(sed("s/o/0/2g", "fooo\n"), "fo00\n");
+        assert_eq!(sed(r"s/\(a*\)b/[\1]/", "aab\n"), "[aa]\n");
+        assert_eq!(sed("s/X/-&-/I", "axb\n"), "a-x-b\n");
+        assert_eq!(sed(r"s/\w\+/\u&/g", "hello world\n"), "Hello World\n");
+        assert_eq!(sed(r"s/.*/\U&\E!/", "up\n"), "UP!\n");
+        assert_eq!(sed("s/x*/-/g", "abc\n"), "-a-b-c-\n");
+        assert_eq!(sed("s|/|:|g", "/a/b\n"), ":a:b\n");
+        assert_eq!(sed("s/[/]/_/", "a/b\n"), "a_b\n");
+        assert_eq!(sed("s/a/\\n/", "bab\n"), "b\nb\n");
+    }
+
+    #[test]
+    fn selects_lines_and_ranges() {
+        let input = "1\n2\n3\n4\n5\n6\n";
+        assert_eq!(sed_with("2p", input, true), "2\n");
+        assert_eq!(sed_with("$p", input, true), "6\n");
+        assert_eq!(sed_with("2,4p", input, true), "2\n3\n4\n");
+        assert_eq!(sed_with("/3/,/5/p", input, true), "3\n4\n5\n");
+        assert_eq!(sed_with("0~2p", input, true), "2\n4\n6\n");
+        assert_eq!(sed_with("2,+1p", input, true), "2\n3\n");
+        assert_eq!(sed_with("5,~4p", input, true), "5\n6\n");
+        assert_eq!(sed_with("4,2p", input, true), "4\n");
+        assert_eq!(sed_with("0,/[0-9]/p", input, true), "1\n");
+        assert_eq!(sed_with("1,/[0-9]/p", input, true), "1\n2\n");
+        assert_eq!(sed("2,5!d", input), "2\n3\n4\n5\n");
+        assert_eq!(sed("/2/,/4/{/3/d}", input), "1\n2\n4\n5\n6\n");
+    }
+
+    #[test]
+    fn runs_text_and_hold_commands() {
+        assert_eq!(sed("1i\\\ntop\n$a end", "a\nb\n"), "top\na\nb\nend\n");
+        assert_eq!(sed("2c\\\nchanged", "a\nb\nc\n"), "a\nchanged\nc\n");
+        assert_eq!(sed("1,2c gone", "a\nb\nc\n"), "gone\nc\n");
+        // tac
+        assert_eq!(sed_with("1!G;h;$p", "a\nb\nc\n", true), "c\nb\na\n");
+        // join lines
+        assert_eq!(sed(":a;N;$!ba;s/\\n/,/g", "a\nb\nc\n"), "a,b,c\n");
+        assert_eq!(sed("$!N;P;D", "a\nb\nc\n"), "a\nb\nc\n");
+        assert_eq!(sed("=", "a\n"), "1\na\n");
+        assert_eq!(sed("y/abc/xyz/", "aabbcc\n"), "xxyyzz\n");
+        assert_eq!(sed("2q", "a\nb\nc\n"), "a\nb\n");
+        assert_eq!(sed("n;d", "a\nb\nc\nd\n"), "a\nc\n");
+        assert_eq!(sed("s/a/x/;ta;s/$/!/;:a", "a\nb\n"), "x\nb!\n");
+        assert_eq!(sed("l", "a\tb\n"), "a\\tb$\na\tb\n");
+        assert_eq!(sed("p", "no newline"), "no newline\nno newline");
+    }
+
+    #[test]
+    fn reports_script_errors() {
+        for script in [
+            "k",
+            "s/a/b",
+            "2,3q",
+            "b nowhere",
+            "{p",
+            "p}",
+            "s/a/b/0",
+            "y/ab/c/",
+        ] {
+            assert!(Script::parse(script, false).is_err(), "{script}");
+        }
+    }
 }
@@ -1,174 +1,438 @@
-//! `seq` builtin - output a sequence of numbers.
+//! `seq` builtin - print a sequence of numbers
 //!
-//! Usage:
-//!   seq LAST                    # from 1 to LAST
-//!   seq FIRST LAST              # from FIRST to LAST
-//!   seq FIRST INCREMENT LAST    # from FIRST to LAST by INCREMENT
+//! Syntax:
+//!   seq [-w] [-f FORMAT] [-s SEP] [FIRST [INCREMENT]] LAST
 //!
-//! Options:
-//!   -s STRING    Use STRING as separator (default: newline)
-//!   -w           Pad numbers with leading zeros to equal width
-//!   -f FORMAT    Use printf-style floating-point FORMAT (default: %g)
+//! Numbers run from FIRST, 1 by default, in steps of INCREMENT, also 1 by
+//! default, up to LAST, or down to it when INCREMENT is negative. Plain
+//! decimal operands are stepped exactly, so `seq 0 0.1 1` ends on 1.0, and
+//! print with as many decimals as FIRST and INCREMENT have. `-f` formats
+//! each number with a printf floating point conversion, `-s` separates them
+//! with SEP instead of a newline and `-w` pads them with leading zeros to an
+//! equal width.
+//!
+//! The exit status is 0 on success and 1 on an invalid option or operand.
+
+use crate::awk::format_number;
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
 
-use anyhow::{anyhow, Result};
+/// The `seq` builtin command implementation
+pub struct SeqCommand;
 
-/// Entry point for the seq builtin.
-pub fn seq_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("seq: missing operand"));
+impl Builtin for SeqCommand {
+    fn name(&self) -> &'static str {
+        "seq"
     }
 
-    let mut separator = "\n".to_string();
-    let mut equal_width = false;
-    let mut format = "%g".to_string();
-    let mut args_iter = args.iter();
-    let mut numbers = Vec::new();
+    fn synopsis(&self) -> &'static str {
+        "Print a sequence of numbers"
+    }
 
-    // Parse options and collect numeric arguments
-    while let Some(arg) = args_iter.next() {
-        match arg.as_str() {
-            "-s" => {
-                separator = args_iter
-                    .next()
-                    .ok_or_else(|| anyhow!("seq: option requires an argument -- s"))?
-                    .clone();
-            }
-            "-w" => {
-                equal_width = true;
-            }
-            "-f" => {
-                format = args_iter
-                    .next()
-                    .ok_or_else(|| anyhow!("seq: option requires an argument -- f"))?
-                    .clone();
-            }
-            _ if arg.starts_with('-') => {
-                return Err(anyhow!("seq: invalid option -- '{}'", arg.trim_start_matches('-')));
-            }
-            _ => {
-                numbers.push(arg.parse::<f64>()
-                    .map_err(|_| anyhow!("seq: invalid floating point argument: '{}'", arg))?);
-            }
-        }
+    fn description(&self) -> &'static str {
+        "Print the numbers from FIRST to LAST in steps of INCREMENT, one per line or \
+         separated by SEP with -s."
     }
 
-    if numbers.is_empty() {
-        return Err(anyhow!("seq: missing operand"));
+    fn usage(&self) -> &'static str {
+        "seq [-w] [-f FORMAT] [-s SEP] [FIRST [INCREMENT]] LAST"
     }
 
-    let (first, increment, last) = match numbers.len() {
-        1 => (1.0, 1.0, numbers[0]),
-        2 => (numbers[0], 1.0, numbers[1]),
-        3 => (numbers[0], numbers[1], numbers[2]),
-        _ => return Err(anyhow!("seq: too many operands")),
-    };
+    fn help(&self) -> &'static str {
+        "Print a sequence of numbers. Use 'seq -w 1 10' for zero padded numbers or \
+         'seq -s, 0 0.5 2' for a comma separated list."
+    }
 
-    if increment == 0.0 {
-        return Err(anyhow!("seq: increment cannot be zero"));
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
+}
+
+/// Run seq for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
 
-    // Generate sequence
-    let mut current = first;
-    let mut output = String::new();
-    let mut count = 0;
+/// A numeric operand
+#[derive(Debug, Clone, Copy)]
+struct Number {
+    value: f64,
+    /// A plain decimal as an integer and the power of ten it is scaled by
+    exact: Option<(i128, u32)>,
+    /// Digits after the decimal point, unknown for exponent forms
+    decimals: Option<usize>,
+}
 
-    // Calculate maximum width for padding if -w is specified
-    let max_width = if equal_width {
-        let max_val = if increment > 0.0 { last } else { first };
-        let min_val = if increment > 0.0 { first } else { last };
-        format_number(max_val.max(min_val.abs()), &format).len()
-    } else {
-        0
+impl Number {
+    const ONE: Number = Number {
+        value: 1.0,
+        exact: Some((1, 0)),
+        decimals: Some(0),
     };
 
-    while (increment > 0.0 && current <= last) || (increment < 0.0 && current >= last) {
-        if count > 0 {
-            output.push_str(&separator);
+    fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid floating point argument: '{text}'");
+        let value: f64 = text.parse().map_err(|_| invalid())?;
+        if value.is_nan() {
+            return Err(format!("invalid 'not-a-number' argument: '{text}'"));
         }
-
-        let formatted = format_number(current, &format);
-        if equal_width && max_width > formatted.len() {
-            let padding = max_width - formatted.len();
-            for _ in 0..padding {
-                output.push('0');
-            }
+        let unsigned = text.strip_prefix(['+', '-']).unwrap_or(text);
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let plain = whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit());
+        if !plain {
+            return Ok(Number {
+                value,
+                exact: None,
+                decimals: None,
+            });
         }
-        output.push_str(&formatted);
+        let exact = format!("{whole}{fraction}")
+            .parse::<i128>()
+            .ok()
+            .map(|mantissa| {
+                let mantissa = if text.starts_with('-') {
+                    -mantissa
+                } else {
+                    mantissa
+                };
+                (mantissa, fraction.len() as u32)
+            });
+        Ok(Number {
+            value,
+            exact,
+            decimals: Some(fraction.len()),
+        })
+    }
+}
 
-        current += increment;
-        count += 1;
+#[derive(Debug)]
+struct Options {
+    format: Option<String>,
+    separator: String,
+    equal_width: bool,
+    first: Number,
+    step: Number,
+    last: Number,
+}
 
-        // Prevent infinite loops with floating point precision issues
-        if count > 1_000_000 {
-            return Err(anyhow!("seq: sequence too long"));
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut format = None;
+        let mut separator = "\n".to_string();
+        let mut equal_width = false;
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--equal-width" => equal_width = true,
+                "--format" | "--separator" => {
+                    let value = iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '{arg}' requires an argument"))?;
+                    match arg.as_str() {
+                        "--format" => format = Some(value),
+                        _ => separator = value,
+                    }
+                }
+                long if long.starts_with("--format=") => {
+                    format = Some(long["--format=".len()..].to_string());
+                }
+                long if long.starts_with("--separator=") => {
+                    separator = long["--separator=".len()..].to_string();
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                // Negative numbers are operands rather than options
+                negative
+                    if negative.starts_with('-')
+                        && negative[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.') =>
+                {
+                    operands.push(arg.clone());
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'w' => equal_width = true,
+                            'f' | 's' => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached.to_string(),
+                                };
+                                match flag {
+                                    'f' => format = Some(value),
+                                    _ => separator = value,
+                                }
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
+        if operands.is_empty() {
+            return Err("missing operand".to_string());
         }
+        if let Some(extra) = operands.get(3) {
+            return Err(format!("extra operand '{extra}'"));
+        }
+        let numbers = operands
+            .iter()
+            .map(|operand| Number::parse(operand))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (first, step, last) = match numbers[..] {
+            [last] => (Number::ONE, Number::ONE, last),
+            [first, last] => (first, Number::ONE, last),
+            [first, step, last] => (first, step, last),
+            _ => unreachable!("between one and three operands"),
+        };
+        if step.value == 0.0 {
+            return Err(format!("invalid Zero increment value: '{}'", operands[1]));
+        }
+        if let Some(format) = &format {
+            if equal_width {
+                return Err(
+                    "format string may not be specified when printing equal width strings"
+                        .to_string(),
+                );
+            }
+            check_format(format)?;
+        }
+        Ok(Options {
+            format,
+            separator,
+            equal_width,
+            first,
+            step,
+            last,
+        })
     }
+}
 
-    if !output.is_empty() {
-        println!("{output}");
+/// Make sure FORMAT has exactly one floating point conversion
+fn check_format(format: &str) -> Result<(), String> {
+    let mut conversions = 0;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars
+            .by_ref()
+            .find(|&c| !matches!(c, '-' | '+' | ' ' | '#' | '.' | '0'..='9'))
+        {
+            Some('%') => {}
+            Some('e' | 'E' | 'f' | 'F' | 'g' | 'G') => conversions += 1,
+            Some(other) => {
+                return Err(format!("format '{format}' has unknown %{other} directive"));
+            }
+            None => return Err(format!("format '{format}' ends in %")),
+        }
     }
+    match conversions {
+        0 => Err(format!("format '{format}' has no % directive")),
+        1 => Ok(()),
+        _ => Err(format!("format '{format}' has too many % directives")),
+    }
+}
+
+/// FIRST, STEP and LAST as integers at the largest of their scales, when all
+/// three are plain decimals that fit
+fn scaled(first: Number, step: Number, last: Number) -> Option<(i128, i128, i128, u32)> {
+    let parts = [first.exact?, step.exact?, last.exact?];
+    let scale = parts.iter().map(|&(_, scale)| scale).max()?;
+    // `fixed` divides by powers of ten up to 10^scale
+    10i128.checked_pow(scale)?;
+    let rescale =
+        |(mantissa, from): (i128, u32)| mantissa.checked_mul(10i128.checked_pow(scale - from)?);
+    Some((
+        rescale(parts[0])?,
+        rescale(parts[1])?,
+        rescale(parts[2])?,
+        scale,
+    ))
+}
 
-    Ok(())
+/// VALUE, an integer scaled by 10^SCALE, with DECIMALS digits after the point
+fn fixed(value: i128, scale: u32, decimals: usize) -> String {
+    let value = value / 10i128.pow(scale - decimals as u32);
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    match decimals {
+        0 => format!("{sign}{magnitude}"),
+        _ => {
+            let unit = 10u128.pow(decimals as u32);
+            format!("{sign}{}.{:0decimals$}", magnitude / unit, magnitude % unit)
+        }
+    }
 }
 
-fn format_number(num: f64, format: &str) -> String {
-    // Simple format support - can be extended for more printf-style formats
-    match format {
-        "%g" | "%G" => {
-            if num.fract() == 0.0 && num.abs() < 1e15 {
-                format!("{num:.0}")
-            } else {
-                format!("{num}")
+/// The numbers of the sequence, formatted
+fn sequence(options: &Options) -> Vec<String> {
+    let (first, step, last) = (options.first, options.step, options.last);
+    let decimals = first.decimals.zip(step.decimals).map(|(a, b)| a.max(b));
+    let mut numbers = Vec::new();
+    match (scaled(first, step, last), decimals) {
+        (Some((mut value, step, last, scale)), Some(decimals)) => {
+            let divisor = 10f64.powi(scale as i32);
+            while (step > 0 && value <= last) || (step < 0 && value >= last) {
+                numbers.push(match &options.format {
+                    Some(format) => format_number(format, value as f64 / divisor),
+                    None => fixed(value, scale, decimals),
+                });
+                match value.checked_add(step) {
+                    Some(next) => value = next,
+                    None => break,
+                }
             }
         }
-        "%f" => format!("{num:.6}"),
-        "%e" => format!("{num:e}"),
-        "%E" => format!("{num:E}"),
         _ => {
-            // Try to parse custom format like "%.2f"
-            if format.starts_with("%.") && format.ends_with('f') {
-                if let Ok(precision) = format[2..format.len()-1].parse::<usize>() {
-                    return format!("{num:.precision$}");
+            let format = options.format.clone().unwrap_or_else(|| match decimals {
+                Some(decimals) => format!("%.{decimals}f"),
+                None => "%g".to_string(),
+            });
+            for i in 0u64.. {
+                // Multiplying rather than adding keeps rounding errors from
+                // piling up over a long sequence
+                let value = first.value + i as f64 * step.value;
+                if (step.value > 0.0 && value > last.value)
+                    || (step.value < 0.0 && value < last.value)
+                {
+                    break;
+                }
+                numbers.push(format_number(&format, value));
+                if value.is_infinite() {
+                    break;
                 }
             }
-            format!("{num}")
         }
     }
+    numbers
+}
+
+/// Pad NUMBERS with zeros after any sign to the width of the widest
+fn equalize(numbers: &mut [String]) {
+    let width = numbers.iter().map(String::len).max().unwrap_or(0);
+    for number in numbers {
+        let at = usize::from(number.starts_with('-'));
+        let fill = "0".repeat(width - number.len());
+        number.insert_str(at, &fill);
+    }
+}
+
+fn run(args: &[String]) -> Outcome {
+    match Options::parse(args) {
+        Ok(options) => {
+            let mut numbers = sequence(&options);
+            if options.equal_width {
+                equalize(&mut numbers);
+            }
+            let mut stdout = numbers.join(&options.separator);
+            if !numbers.is_empty() {
+                stdout.push('\n');
+            }
+            Outcome {
+                stdout: stdout.into_bytes(),
+                stderr: Vec::new(),
+                status: 0,
+            }
+        }
+        Err(message) => Outcome {
+            stdout: Vec::new(),
+            stderr: format!("seq: {message}\n").into_bytes(),
+            status: 1,
+        },
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_seq_single_arg() {
-        let result = seq_cli(&["3".to_string()]);
-        assert!(result.is_ok());
+    fn seq(args: &[&str]) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args);
+        let text = if outcome.status == 0 {
+            outcome.stdout
+        } else {
+            outcome.stderr
+        };
+        (String::from_utf8(text).unwrap(), outcome.status)
     }
 
     #[test]
-    fn test_seq_two_args() {
-        let result = seq_cli(&["2".to_string(), "5".to_string()]);
-        assert!(result.is_ok());
+    fn counts_between_operands() {
+        assert_eq!(seq(&["3"]).0, "1\n2\n3\n");
+        assert_eq!(seq(&["2", "4"]).0, "2\n3\n4\n");
+        assert_eq!(seq(&["1", "2", "6"]).0, "1\n3\n5\n");
+        assert_eq!(seq(&["5", "-2", "1"]).0, "5\n3\n1\n");
+        assert_eq!(seq(&["3", "1"]).0, "");
+        assert_eq!(seq(&["-1", "1"]).0, "-1\n0\n1\n");
     }
 
     #[test]
-    fn test_seq_three_args() {
-        let result = seq_cli(&["1".to_string(), "2".to_string(), "10".to_string()]);
-        assert!(result.is_ok());
+    fn steps_decimals_exactly() {
+        assert_eq!(seq(&["0", "0.1", "0.3"]).0, "0.0\n0.1\n0.2\n0.3\n");
+        assert_eq!(seq(&["1", "0.25", "1.5"]).0, "1.00\n1.25\n1.50\n");
+        assert_eq!(seq(&["-0.5", "0.5", "0"]).0, "-0.5\n0.0\n");
+        assert_eq!(seq(&["1e1", "5", "20"]).0, "10\n15\n20\n");
     }
 
     #[test]
-    fn test_format_number() {
-        assert_eq!(format_number(1.0, "%g"), "1");
-        assert_eq!(format_number(1.5, "%g"), "1.5");
-        assert_eq!(format_number(1.0, "%.2f"), "1.00");
+    fn formats_and_separates() {
+        assert_eq!(seq(&["-f", "%g", "1", "0.5", "2"]).0, "1\n1.5\n2\n");
+        assert_eq!(seq(&["-f", "%.2f", "1"]).0, "1.00\n");
+        assert_eq!(seq(&["-f", "n%03g%%", "2"]).0, "n001%\nn002%\n");
+        assert_eq!(seq(&["-s", ",", "3"]).0, "1,2,3\n");
+        assert_eq!(seq(&["-s", "", "3"]).0, "123\n");
+        assert_eq!(seq(&["-w", "8", "10"]).0, "08\n09\n10\n");
+        assert_eq!(seq(&["-w", "-1", "1"]).0, "-1\n00\n01\n");
     }
-} 
-
 
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+    #[test]
+    fn rejects_bad_operands() {
+        assert_eq!(
+            seq(&["1", "0", "5"]),
+            ("seq: invalid Zero increment value: '0'\n".into(), 1)
+        );
+        assert_eq!(
+            seq(&["abc"]),
+            ("seq: invalid floating point argument: 'abc'\n".into(), 1)
+        );
+        assert_eq!(
+            seq(&["-f", "%d", "3"]),
+            ("seq: format '%d' has unknown %d directive\n".into(), 1)
+        );
+        assert_eq!(seq(&[]).1, 1);
+        assert_eq!(seq(&["1", "2", "3", "4"]).1, 1);
+    }
 }
@@ -1,111 +1,163 @@
-use anyhow::Result;
-use nxsh_core::context::ShellContext;
-use crate::common::{BuiltinResult, BuiltinContext};
-
-/// Handle `set` builtin for flags -e, -x, -o pipefail.
-pub fn set_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    if args.is_empty() {
-        if let Ok(opts_guard) = ctx.options.read() {
-            println!("-e {}", opts_guard.errexit);
-            println!("-x {}", opts_guard.xtrace);
-            println!("pipefail {}", opts_guard.pipefail);
-        }
-        return Ok(());
+//! `set` builtin - change shell options and positional parameters
+//!
+//! Syntax:
+//!   set                  # print shell variables
+//!   set -eux             # enable options by flag letter (`+eux` disables)
+//!   set -o NAME          # enable option by long name (`+o NAME` disables)
+//!   set -o               # list options as `name on|off`
+//!   set +o               # list options as re-usable `set` commands
+//!   set -- ARG...        # replace the positional parameters
+//!
+//! Options live in `ShellContext::options` and are honoured by the executor
+//! (`errexit`, `nounset`, `xtrace`, `pipefail`, ...).
+
+use crate::common::usage_error;
+use nxsh_core::context::{ShellContext, ShellOptions};
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+
+/// The `set` builtin command implementation
+pub struct SetCommand;
+
+impl Builtin for SetCommand {
+    fn name(&self) -> &'static str {
+        "set"
     }
 
-    let mut iter = args.iter();
-    while let Some(arg) = iter.next() {
-        match arg.as_str() {
-            "-e" => {
-                if let Ok(mut opts_guard) = ctx.options.write() {
-                    opts_guard.errexit = true;
-                }
-            },
-            "+e" => {
-                if let Ok(mut opts_guard) = ctx.options.write() {
-                    opts_guard.errexit = false;
-                }
-            },
-            "-x" => {
-                if let Ok(mut opts_guard) = ctx.options.write() {
-                    opts_guard.xtrace = true;
+    fn synopsis(&self) -> &'static str {
+        "Set or unset shell options and positional parameters"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change the value of shell options such as errexit (-e), nounset (-u), \
+         xtrace (-x) and pipefail (-o pipefail), or replace the positional parameters."
+    }
+
+    fn usage(&self) -> &'static str {
+        "set [-eufvxCHhm] [-o option-name] [--] [arg ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Set or unset shell options. Use '-' to enable and '+' to disable an option."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // set modifies shell options
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        if args.is_empty() {
+            return Ok(list_variables(ctx));
+        }
+
+        let mut i = 0;
+        let mut positional = None;
+        while i < args.len() {
+            let arg = args[i].as_str();
+            let enable = arg.starts_with('-');
+            match arg {
+                "--" => {
+                    positional = Some(&args[i + 1..]);
+                    break;
                 }
-            },
-            "+x" => {
-                if let Ok(mut opts_guard) = ctx.options.write() {
-                    opts_guard.xtrace = false;
+                "-" => {
+                    // Historical form: turn off -v and -x, then take positionals
+                    ctx.set_option("verbose", false)?;
+                    ctx.set_option("xtrace", false)?;
+                    positional = Some(&args[i + 1..]);
+                    break;
                 }
-            },
-            "-o" => {
-                if let Some(name) = iter.next() {
-                    if name == "pipefail" {
-                        if let Ok(mut opts_guard) = ctx.options.write() {
-                            opts_guard.pipefail = true;
+                "-o" | "+o" => match args.get(i + 1) {
+                    Some(name) => {
+                        if !ShellOptions::SET_OPTIONS.contains(&name.as_str()) {
+                            return Ok(usage_error(&format!("set: {name}: invalid option name")));
                         }
+                        ctx.set_option(name, enable)?;
+                        i += 1;
                     }
-                }
-            }
-            "+o" => {
-                if let Some(name) = iter.next() {
-                    if name == "pipefail" {
-                        if let Ok(mut opts_guard) = ctx.options.write() {
-                            opts_guard.pipefail = false;
+                    None => return Ok(list_options(ctx, enable)),
+                },
+                _ if (enable || arg.starts_with('+')) && arg.len() > 1 => {
+                    for letter in arg[1..].chars() {
+                        if ctx.set_option(&letter.to_string(), enable).is_err() {
+                            let sign = if enable { '-' } else { '+' };
+                            return Ok(usage_error(&format!(
+                                "set: {sign}{letter}: invalid option"
+                            )));
                         }
                     }
                 }
+                _ => {
+                    positional = Some(&args[i..]);
+                    break;
+                }
             }
-            _ => println!("unknown option {}", arg),
+            i += 1;
+        }
+
+        if let Some(params) = positional {
+            ctx.set_positional_args(params.to_vec());
         }
+        Ok(ExecutionResult::success(0))
     }
-    Ok(())
 }
 
-/// Execute the set builtin command
-pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    if args.is_empty() {
-        // Print all environment variables if no arguments
-        for (key, value) in std::env::vars() {
-            println!("{}={}", key, value);
-        }
-        return Ok(0);
+impl SetCommand {
+    /// Create a new set command instance
+    pub fn new() -> Self {
+        SetCommand
     }
+}
 
-    // Handle shell options
-    for arg in args {
-        match arg.as_str() {
-            "-e" => {
-                eprintln!("set: -e (errexit) option is not implemented in this context");
-            }
-            "+e" => {
-                eprintln!("set: +e (no errexit) option is not implemented in this context");
-            }
-            "-x" => {
-                eprintln!("set: -x (xtrace) option is not implemented in this context");
-            }
-            "+x" => {
-                eprintln!("set: +x (no xtrace) option is not implemented in this context");
-            }
-            "-o" => {
-                eprintln!("set: -o option requires an argument");
-            }
-            "+o" => {
-                eprintln!("set: +o option requires an argument");
-            }
-            _ if arg.starts_with("-o") => {
-                let option = &arg[2..];
-                eprintln!("set: -o {} option is not implemented", option);
-            }
-            _ if arg.starts_with("+o") => {
-                let option = &arg[2..];
-                eprintln!("set: +o {} option is not implemented", option);
-            }
-            _ => {
-                eprintln!("set: invalid option '{}'", arg);
-                return Ok(1);
+impl Default for SetCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `set` with no arguments: every shell variable as `name=value`, sorted
+fn list_variables(ctx: &ShellContext) -> ExecutionResult {
+    let mut names: Vec<(String, String)> = Vec::new();
+    if let Ok(env) = ctx.env.read() {
+        names.extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    if let Ok(vars) = ctx.vars.read() {
+        for (k, var) in vars.iter() {
+            if !names.iter().any(|(n, _)| n == k) {
+                names.push((k.clone(), var.value.clone()));
             }
         }
     }
+    names.sort();
+    let mut out = String::new();
+    for (name, value) in names {
+        out.push_str(&format!("{name}={}\n", quote_value(&value)));
+    }
+    ExecutionResult::success(0).with_output(out.into_bytes())
+}
 
-    Ok(0)
-} 
+/// `set -o` (human readable) or `set +o` (re-usable commands)
+fn list_options(ctx: &ShellContext, human: bool) -> ExecutionResult {
+    let options = ctx.get_all_options().unwrap_or_default();
+    let mut out = String::new();
+    for name in ShellOptions::SET_OPTIONS {
+        let on = options.get(name).unwrap_or(false);
+        if human {
+            out.push_str(&format!("{name:<15}\t{}\n", if on { "on" } else { "off" }));
+        } else {
+            out.push_str(&format!("set {}o {name}\n", if on { '-' } else { '+' }));
+        }
+    }
+    ExecutionResult::success(0).with_output(out.into_bytes())
+}
 
+fn quote_value(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:,@%+=".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
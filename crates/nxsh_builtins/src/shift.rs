@@ -1,34 +1,74 @@
-//! `shift` builtin  Eshift positional parameters left by N.
-//! For initial implementation, positional parameters are stored in
-//! `__ARGV` variable within `ShellContext` as a NUL-separated list.
-//! `shift [N]` removes first N elements.
+//! `shift` builtin - shift positional parameters left by N (default 1).
+//!
+//! `$N+1` becomes `$1`; `$0` is never shifted. Shifting by more than `$#`
+//! fails and leaves the parameters unchanged.
 
-use anyhow::{anyhow, Result};
 use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 
-const ARGV_KEY: &str = "__ARGV";
+/// The `shift` builtin command implementation
+pub struct ShiftCommand;
 
-pub fn shift_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    let n = if args.is_empty() { 1 } else { args[0].parse::<usize>().unwrap_or(1) };
-    let argv_raw = ctx.get_var(ARGV_KEY).unwrap_or_default();
-    let mut parts: Vec<&str> = argv_raw.split('\0').filter(|s| !s.is_empty()).collect();
-    if n > parts.len() {
-        return Err(anyhow!("shift: not enough positional parameters"));
+impl Builtin for ShiftCommand {
+    fn name(&self) -> &'static str {
+        "shift"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Shift positional parameters"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rename the positional parameters $N+1,... to $1,... If N is not given, it is assumed to be 1."
+    }
+
+    fn usage(&self) -> &'static str {
+        "shift [n]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Shift positional parameters left by N (default 1)."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // shift modifies the positional parameters
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let count = match args {
+            [] => 1,
+            [n] => match n.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => {
+                    return Ok(ExecutionResult::failure(1).with_error(
+                        format!("shift: {n}: numeric argument required\n").into_bytes(),
+                    ))
+                }
+            },
+            _ => {
+                return Ok(
+                    ExecutionResult::failure(2).with_error(b"shift: too many arguments\n".to_vec())
+                )
+            }
+        };
+        if ctx.shift_positional(count) {
+            Ok(ExecutionResult::success(0))
+        } else {
+            // bash fails silently here; the status tells the script
+            Ok(ExecutionResult::failure(1))
+        }
     }
-    parts.drain(0..n);
-    ctx.set_var(ARGV_KEY, parts.join("\0"));
-    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn shift_once() {
-        let ctx = ShellContext::new();
-        ctx.set_var(ARGV_KEY, "a\0b\0c".to_string());
-        shift_cli(&[], &ctx).unwrap();
-        assert_eq!(ctx.get_var(ARGV_KEY).unwrap(), "b\0c");
+impl ShiftCommand {
+    /// Create a new shift command instance
+    pub fn new() -> Self {
+        ShiftCommand
     }
-} 
+}
 
+impl Default for ShiftCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
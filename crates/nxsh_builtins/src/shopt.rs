@@ -0,0 +1,133 @@
+//! `shopt` builtin - toggle optional shell behaviour
+//!
+//! Syntax:
+//!   shopt                # list shopt options as `name on|off`
+//!   shopt -s NAME...     # enable
+//!   shopt -u NAME...     # disable
+//!   shopt -q NAME...     # exit 0 only if all are enabled, print nothing
+//!   shopt -p [NAME...]   # print as re-usable `shopt` commands
+//!   shopt -o ...         # operate on `set -o` options instead
+
+use crate::common::usage_error;
+use nxsh_core::context::{ShellContext, ShellOptions};
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+
+/// The `shopt` builtin command implementation
+pub struct ShoptCommand;
+
+impl Builtin for ShoptCommand {
+    fn name(&self) -> &'static str {
+        "shopt"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Set and unset shell options"
+    }
+
+    fn description(&self) -> &'static str {
+        "Toggle optional shell behaviour such as extglob, nullglob, dotglob, cdspell and correct. \
+         With -o, operate on the options understood by 'set -o'."
+    }
+
+    fn usage(&self) -> &'static str {
+        "shopt [-pqsu] [-o] [optname ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Set and unset shell options. Without -s or -u, show the current settings."
+    }
+
+    fn affects_shell_state(&self) -> bool {
+        true // shopt modifies shell options
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut enable = None;
+        let mut quiet = false;
+        let mut reusable = false;
+        let mut set_names = false;
+        let mut rest = args;
+        while let Some(flag) = rest.first() {
+            if flag == "--" {
+                rest = &rest[1..];
+                break;
+            }
+            if !flag.starts_with('-') || flag.len() == 1 {
+                break;
+            }
+            for letter in flag[1..].chars() {
+                match letter {
+                    's' => enable = Some(true),
+                    'u' => enable = Some(false),
+                    'q' => quiet = true,
+                    'p' => reusable = true,
+                    'o' => set_names = true,
+                    _ => {
+                        return Ok(usage_error(&format!(
+                            "shopt: -{letter}: invalid option\nshopt: usage: {}",
+                            self.usage()
+                        )))
+                    }
+                }
+            }
+            rest = &rest[1..];
+        }
+
+        let known = if set_names {
+            ShellOptions::SET_OPTIONS
+        } else {
+            ShellOptions::SHOPT_OPTIONS
+        };
+        if let Some(name) = rest.iter().find(|n| !known.contains(&n.as_str())) {
+            return Ok(ExecutionResult::failure(1)
+                .with_error(format!("shopt: {name}: invalid shell option name\n").into_bytes()));
+        }
+
+        if let Some(value) = enable {
+            for name in rest {
+                ctx.set_option(name, value)?;
+            }
+            return Ok(ExecutionResult::success(0));
+        }
+
+        let options = ctx.get_all_options()?;
+        let names: Vec<&str> = if rest.is_empty() {
+            known.to_vec()
+        } else {
+            rest.iter().map(String::as_str).collect()
+        };
+        let mut out = String::new();
+        let mut all_on = true;
+        for name in names {
+            let on = options.get(name).unwrap_or(false);
+            all_on &= on;
+            if reusable {
+                let flag = if on { "-s" } else { "-u" };
+                let o = if set_names { " -o" } else { "" };
+                out.push_str(&format!("shopt {flag}{o} {name}\n"));
+            } else {
+                out.push_str(&format!("{name:<15}\t{}\n", if on { "on" } else { "off" }));
+            }
+        }
+
+        // Querying named options reports whether they are all enabled
+        let status = if rest.is_empty() || all_on { 0 } else { 1 };
+        if quiet {
+            return Ok(ExecutionResult::success(status));
+        }
+        Ok(ExecutionResult::success(status).with_output(out.into_bytes()))
+    }
+}
+
+impl ShoptCommand {
+    /// Create a new shopt command instance
+    pub fn new() -> Self {
+        ShoptCommand
+    }
+}
+
+impl Default for ShoptCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,472 @@
+//! `shuf` builtin - write a random permutation of the input lines
+//!
+//! Syntax:
+//!   shuf [-rz] [-n COUNT] [-o FILE] [--random-source=FILE] [FILE]
+//!   shuf -e [-rz] [-n COUNT] [-o FILE] [--random-source=FILE] [ARG...]
+//!   shuf -i LO-HI [-rz] [-n COUNT] [-o FILE] [--random-source=FILE]
+//!
+//! The lines of FILE, standard input when there is none or it is `-`, are
+//! written in random order. `-e` shuffles the ARGs instead and `-i` the
+//! numbers LO to HI, without listing them first, so `shuf -i 1-1000000000
+//! -n 3` is cheap. `-n` stops after COUNT lines and `-r` picks each line
+//! independently, repeating lines and needing `-n` to end. `-o` writes to
+//! FILE instead of standard output and `-z` ends lines with NUL.
+//!
+//! `--random-source` draws the randomness from the bytes of FILE instead of
+//! the system, so the same FILE always gives the same order.
+//!
+//! The exit status is 0 on success and 1 on any error.
+
+use crate::common::operands::Inputs;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use rand::{rngs::OsRng, RngCore};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+/// The `shuf` builtin command implementation
+pub struct ShufCommand;
+
+impl Builtin for ShufCommand {
+    fn name(&self) -> &'static str {
+        "shuf"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Write a random permutation of the input lines"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write the lines of FILE, the ARGs with -e or the numbers LO to HI with -i in \
+         random order; -n limits the count and -r allows repeats."
+    }
+
+    fn usage(&self) -> &'static str {
+        "shuf [-erz] [-i LO-HI] [-n COUNT] [-o FILE] [--random-source=FILE] [FILE | ARG...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Write a random permutation of the input lines. Use 'shuf -n 1 names' to pick a \
+         random line or 'shuf -i 1-6 -rn 10' to roll ten dice."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run shuf for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// Where the lines to shuffle come from
+#[derive(Debug)]
+enum Input {
+    File(String),
+    Echo(Vec<String>),
+    /// The first number and how many follow it
+    Range(u64, u64),
+}
+
+#[derive(Debug)]
+struct Options {
+    input: Input,
+    count: Option<u64>,
+    output: Option<String>,
+    repeat: bool,
+    delimiter: u8,
+    random_source: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut echo = false;
+        let mut repeat = false;
+        let mut delimiter = b'\n';
+        let mut values = Vec::new();
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    let (name, attached) = match long[2..].split_once('=') {
+                        Some((name, value)) => (name, Some(value.to_string())),
+                        None => (&long[2..], None),
+                    };
+                    let flag = match name {
+                        "echo" => 'e',
+                        "repeat" => 'r',
+                        "zero-terminated" => 'z',
+                        "head-count" => 'n',
+                        "output" => 'o',
+                        "input-range" => 'i',
+                        "random-source" => 'R',
+                        _ => return Err(format!("unrecognized option '{long}'")),
+                    };
+                    match flag {
+                        'e' => echo = true,
+                        'r' => repeat = true,
+                        'z' => delimiter = b'\0',
+                        _ => {
+                            let value = match attached {
+                                Some(value) => value,
+                                None => iter.next().cloned().ok_or_else(|| {
+                                    format!("option '--{name}' requires an argument")
+                                })?,
+                            };
+                            values.push((flag, value));
+                        }
+                    }
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'e' => echo = true,
+                            'r' => repeat = true,
+                            'z' => delimiter = b'\0',
+                            'n' | 'o' | 'i' => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached.to_string(),
+                                };
+                                values.push((flag, value));
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
+
+        let mut count = None;
+        let mut output = None;
+        let mut range = None;
+        let mut random_source = None;
+        for (flag, value) in values {
+            match flag {
+                'n' => {
+                    count = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid line count: '{value}'"))?,
+                    );
+                }
+                'o' => output = Some(value),
+                'i' => range = Some(parse_range(&value)?),
+                _ => random_source = Some(value),
+            }
+        }
+        let input = match (echo, range) {
+            (true, Some(_)) => return Err("cannot combine -e and -i options".to_string()),
+            (true, None) => Input::Echo(operands),
+            (false, Some((first, len))) => {
+                if let Some(extra) = operands.first() {
+                    return Err(format!("extra operand '{extra}'"));
+                }
+                Input::Range(first, len)
+            }
+            (false, None) => {
+                if let Some(extra) = operands.get(1) {
+                    return Err(format!("extra operand '{extra}'"));
+                }
+                Input::File(operands.pop().unwrap_or_else(|| "-".to_string()))
+            }
+        };
+        if repeat && count.is_none() {
+            return Err("--repeat needs a line count from -n".to_string());
+        }
+        Ok(Options {
+            input,
+            count,
+            output,
+            repeat,
+            delimiter,
+            random_source,
+        })
+    }
+}
+
+/// Parse `LO-HI` into the first number and how many there are
+fn parse_range(text: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("invalid input range: '{text}'");
+    let (low, high) = text.split_once('-').ok_or_else(invalid)?;
+    let low: u64 = low.parse().map_err(|_| invalid())?;
+    let high: u64 = high.parse().map_err(|_| invalid())?;
+    // HI may be one less than LO for an empty range
+    let len = high
+        .checked_add(1)
+        .and_then(|end| end.checked_sub(low))
+        .ok_or_else(invalid)?;
+    Ok((low, len))
+}
+
+/// Where random numbers come from
+enum Random {
+    /// A splitmix64 generator seeded by the system
+    Seeded(u64),
+    /// Eight bytes of a `--random-source` file per number
+    Source { name: String, reader: Box<dyn Read> },
+}
+
+impl Random {
+    fn next(&mut self) -> Result<u64, String> {
+        match self {
+            Random::Seeded(state) => {
+                *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                Ok(z ^ (z >> 31))
+            }
+            Random::Source { name, reader } => {
+                let mut bytes = [0; 8];
+                reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => format!("{name}: end of file"),
+                    _ => format!("{name}: {}", io_message(&e)),
+                })?;
+                Ok(u64::from_le_bytes(bytes))
+            }
+        }
+    }
+
+    /// A uniform number below N, which must not be zero
+    fn below(&mut self, n: u64) -> Result<u64, String> {
+        // Draws from the incomplete block at the top would favour small
+        // results, so they are thrown away
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let drawn = self.next()?;
+            if drawn < limit {
+                return Ok(drawn % n);
+            }
+        }
+    }
+}
+
+/// The first COUNT entries of a random permutation of 0..N. Swaps are kept
+/// in a map so a huge range costs only what is picked from it.
+fn permute(n: u64, count: u64, random: &mut Random) -> Result<Vec<u64>, String> {
+    let mut moved = HashMap::new();
+    let mut picked = Vec::new();
+    for i in 0..count.min(n) {
+        let j = i + random.below(n - i)?;
+        let at_i = moved.get(&i).copied().unwrap_or(i);
+        picked.push(moved.insert(j, at_i).unwrap_or(j));
+    }
+    Ok(picked)
+}
+
+/// The lines being shuffled
+enum Items {
+    Lines(Vec<Vec<u8>>),
+    Range(u64, u64),
+}
+
+impl Items {
+    fn len(&self) -> u64 {
+        match self {
+            Items::Lines(lines) => lines.len() as u64,
+            Items::Range(_, len) => *len,
+        }
+    }
+
+    fn write(&self, index: u64, out: &mut Vec<u8>) {
+        match self {
+            Items::Lines(lines) => out.extend_from_slice(&lines[index as usize]),
+            Items::Range(first, _) => out.extend_from_slice((first + index).to_string().as_bytes()),
+        }
+    }
+}
+
+/// The shuffled lines, or nothing when they went to an `-o` file
+fn shuffle(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Result<Vec<u8>, String> {
+    let options = Options::parse(args)?;
+    let items = match options.input {
+        Input::File(name) => {
+            let data = Inputs::new(cwd, stdin)
+                .read(&name)
+                .map_err(|e| format!("{name}: {}", io_message(&e)))?;
+            let mut lines: Vec<Vec<u8>> = data
+                .split(|&byte| byte == options.delimiter)
+                .map(<[u8]>::to_vec)
+                .collect();
+            if lines.last().is_some_and(Vec::is_empty) {
+                lines.pop();
+            }
+            Items::Lines(lines)
+        }
+        Input::Echo(words) => Items::Lines(words.into_iter().map(String::into_bytes).collect()),
+        Input::Range(first, len) => Items::Range(first, len),
+    };
+    let mut random = match &options.random_source {
+        Some(name) => {
+            let file =
+                File::open(cwd.join(name)).map_err(|e| format!("{name}: {}", io_message(&e)))?;
+            Random::Source {
+                name: name.clone(),
+                reader: Box::new(BufReader::new(file)),
+            }
+        }
+        None => Random::Seeded(OsRng.next_u64()),
+    };
+
+    let total = items.len();
+    let picks = match options.count {
+        Some(count) if options.repeat => {
+            if total == 0 && count > 0 {
+                return Err("no lines to repeat".to_string());
+            }
+            (0..count)
+                .map(|_| random.below(total))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        count => permute(total, count.unwrap_or(total), &mut random)?,
+    };
+    let mut out = Vec::new();
+    for index in picks {
+        items.write(index, &mut out);
+        out.push(options.delimiter);
+    }
+    match &options.output {
+        Some(name) => {
+            std::fs::write(cwd.join(name), &out)
+                .map_err(|e| format!("{name}: {}", io_message(&e)))?;
+            Ok(Vec::new())
+        }
+        None => Ok(out),
+    }
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    match shuffle(args, cwd, stdin) {
+        Ok(stdout) => Outcome {
+            stdout,
+            stderr: Vec::new(),
+            status: 0,
+        },
+        Err(message) => Outcome {
+            stdout: Vec::new(),
+            stderr: format!("shuf: {message}\n").into_bytes(),
+            status: 1,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shuf(args: &[&str], input: &str, dir: &Path) -> (String, i32) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, dir, &mut input.as_bytes());
+        let text = if outcome.status == 0 {
+            outcome.stdout
+        } else {
+            outcome.stderr
+        };
+        (String::from_utf8(text).unwrap(), outcome.status)
+    }
+
+    fn sorted(text: &str) -> Vec<&str> {
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    #[test]
+    fn permutes_every_line_once() {
+        let dir = Path::new(".");
+        let (out, status) = shuf(&[], "c\na\nb\n", dir);
+        assert_eq!(status, 0);
+        assert_eq!(sorted(&out), ["a", "b", "c"]);
+
+        let (out, _) = shuf(&["-i", "3-7"], "", dir);
+        assert_eq!(sorted(&out), ["3", "4", "5", "6", "7"]);
+
+        let (out, _) = shuf(&["-e", "x", "y", "-z"], "", dir);
+        assert_eq!(out.len(), 4);
+        assert!(out.ends_with('\0'));
+
+        let (out, _) = shuf(&["-n", "2", "-i", "1-1000000000000"], "", dir);
+        let picked = sorted(&out);
+        assert_eq!(picked.len(), 2);
+        assert_ne!(picked[0], picked[1]);
+
+        assert_eq!(shuf(&["-i", "5-4"], "", dir), (String::new(), 0));
+    }
+
+    #[test]
+    fn repeats_with_a_count() {
+        let dir = Path::new(".");
+        assert_eq!(shuf(&["-r", "-n", "3", "-e", "x"], "", dir).0, "x\nx\nx\n");
+        let (out, _) = shuf(&["-rn", "20", "-i", "1-2"], "", dir);
+        assert_eq!(out.lines().count(), 20);
+        assert!(out.lines().all(|line| line == "1" || line == "2"));
+    }
+
+    #[test]
+    fn random_source_is_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        std::fs::write(dir.path().join("seed"), bytes).unwrap();
+        let args = ["--random-source=seed", "-i", "1-50"];
+        let first = shuf(&args, "", dir.path());
+        assert_eq!(first.1, 0);
+        assert_eq!(shuf(&args, "", dir.path()), first);
+
+        std::fs::write(dir.path().join("short"), [1, 2, 3]).unwrap();
+        assert_eq!(
+            shuf(
+                &["--random-source", "short", "-e", "a", "b"],
+                "",
+                dir.path()
+            ),
+            ("shuf: short: end of file\n".into(), 1)
+        );
+    }
+
+    #[test]
+    fn writes_output_file_and_reports_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(shuf(&["-o", "out", "-e", "only"], "", dir.path()).0, "");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out")).unwrap(),
+            "only\n"
+        );
+        assert_eq!(shuf(&["-e", "-i", "1-2"], "", dir.path()).1, 1);
+        assert_eq!(shuf(&["-r", "-e", "a"], "", dir.path()).1, 1);
+        assert_eq!(
+            shuf(&["-i", "9"], "", dir.path()).0,
+            "shuf: invalid input range: '9'\n"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::{BuiltinContext, BuiltinResult};
+use crate::common::{parse_duration, BuiltinContext, BuiltinResult};
 use std::thread;
 use std::time::Duration;
 
@@ -61,53 +61,6 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
     Ok(0)
 }
 
-fn parse_duration(s: &str) -> Result<Duration, String> {
-    if s.is_empty() {
-        return Err("invalid time interval".to_string());
-    }
-
-    // Handle suffixes
-    let (number_str, suffix) = if let Some(stripped) = s.strip_suffix('s') {
-        (stripped, "s")
-    } else if let Some(stripped) = s.strip_suffix('m') {
-        (stripped, "m")
-    } else if let Some(stripped) = s.strip_suffix('h') {
-        (stripped, "h")
-    } else if let Some(stripped) = s.strip_suffix('d') {
-        (stripped, "d")
-    } else {
-        (s, "s") // Default to seconds
-    };
-
-    // Parse the number part
-    let number: f64 = number_str
-        .parse()
-        .map_err(|_| format!("invalid time interval '{s}'"))?;
-
-    if number < 0.0 {
-        return Err("invalid time interval".to_string());
-    }
-
-    // Convert to seconds based on suffix
-    let seconds = match suffix {
-        "s" => number,
-        "m" => number * 60.0,
-        "h" => number * 3600.0,
-        "d" => number * 86400.0,
-        _ => return Err(format!("invalid time interval '{s}'")),
-    };
-
-    // Convert to Duration
-    let duration = Duration::from_secs_f64(seconds);
-
-    // Check for reasonable limits (avoid overflow)
-    if seconds > u64::MAX as f64 {
-        return Err("time interval too large".to_string());
-    }
-
-    Ok(duration)
-}
-
 fn print_help() {
     println!("Usage: sleep NUMBER[SUFFIX]...");
     println!("Pause for NUMBER seconds. SUFFIX may be 's' for seconds (the default),");
@@ -1,92 +1,354 @@
-//! `split` command  Esplit a file into pieces.
-//! Usage: split [-b N] FILE [PREFIX]
-//!   -b N : byte size per piece, supports K/M suffix (default 1000000 bytes)
-//! If PREFIX omitted, defaults to "x" producing xa, xb, ...
-
-use anyhow::{anyhow, Result};
-use std::fs::File;
-use std::io::{Read, Write};
+//! `split` builtin - split a file into pieces
+//!
+//! Syntax:
+//!   split [-l LINES | -b SIZE | -n CHUNKS] [-a LEN] [-d] [--additional-suffix=SUF]
+//!         [--verbose] [FILE [PREFIX]]
+//!
+//! FILE (standard input when absent or `-`) is cut into pieces of LINES
+//! lines (1000 by default), of SIZE bytes, or into CHUNKS pieces of equal
+//! size. The pieces are written to PREFIX (`x` by default) followed by a
+//! LEN-character suffix, `aa`, `ab`, ... or `00`, `01`, ... with `-d`.
+//! SIZE takes the multipliers K, M, G, T (powers of 1024) and KB, MB, GB,
+//! TB (powers of 1000).
+//!
+//! The exit status is 0 on success and 1 on any error.
+
+use crate::common::operands::Inputs;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Read, Write};
 use std::path::Path;
-#[cfg(feature = "async-runtime")]
-use tokio::task;
-
-// Synchronous version used when async-runtime disabled (super-min path)
-#[cfg(not(feature = "async-runtime"))]
-pub fn split_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() { return Err(anyhow!("split: missing file operand")); }
-    let mut size: u64 = 1_000_000; // 1 MB default
-    let mut prefix = "x".to_string();
-    let mut idx = 0;
-    if args[0] == "-b" {
-        if args.len() < 3 { return Err(anyhow!("split: invalid usage")); }
-        size = parse_size(&args[1])?;
-        idx = 2;
+
+/// The `split` builtin command implementation
+pub struct SplitCommand;
+
+impl Builtin for SplitCommand {
+    fn name(&self) -> &'static str {
+        "split"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Split a file into pieces"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write FILE in pieces of LINES lines, SIZE bytes or CHUNKS equal parts to \
+         PREFIXaa, PREFIXab, ..."
+    }
+
+    fn usage(&self) -> &'static str {
+        "split [-l LINES | -b SIZE | -n CHUNKS] [-a LEN] [-d] [FILE [PREFIX]]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Split a file into pieces. Use 'split -b 1M big.iso part.' for 1 MiB pieces."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
-    let file_arg = &args[idx];
-    if args.len() > idx + 1 { prefix = args[idx+1].clone(); }
-    split_file(Path::new(file_arg).to_path_buf(), prefix, size)
 }
 
-#[cfg(feature = "async-runtime")]
-pub async fn split_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() { return Err(anyhow!("split: missing file operand")); }
-    let mut size: u64 = 1_000_000; // 1 MB default
-    let mut prefix = "x".to_string();
-    let mut idx = 0;
-    if args[0] == "-b" {
-        if args.len() < 3 { return Err(anyhow!("split: invalid usage")); }
-        size = parse_size(&args[1])?;
-        idx = 2;
+/// Run split for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// How the input is divided
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pieces {
+    Lines(usize),
+    Bytes(usize),
+    Chunks(usize),
+}
+
+#[derive(Debug)]
+struct Options {
+    pieces: Pieces,
+    suffix_length: usize,
+    numeric: bool,
+    additional_suffix: String,
+    verbose: bool,
+    input: String,
+    prefix: String,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            pieces: Pieces::Lines(1000),
+            suffix_length: 2,
+            numeric: false,
+            additional_suffix: String::new(),
+            verbose: false,
+            input: "-".to_string(),
+            prefix: "x".to_string(),
+        };
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                operands.extend(iter.by_ref().cloned());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                match name {
+                    "numeric-suffixes" => options.numeric = true,
+                    "verbose" => options.verbose = true,
+                    "lines" | "bytes" | "number" | "suffix-length" | "additional-suffix" => {
+                        let value = match inline {
+                            Some(value) => value,
+                            None => iter
+                                .next()
+                                .cloned()
+                                .ok_or_else(|| format!("option '--{name}' requires an argument"))?,
+                        };
+                        options.set(name, &value)?;
+                    }
+                    _ => return Err(format!("unrecognized option '{arg}'")),
+                }
+                continue;
+            }
+            if arg.len() < 2 || !arg.starts_with('-') {
+                operands.push(arg.clone());
+                continue;
+            }
+            for (at, flag) in arg[1..].char_indices() {
+                let name = match flag {
+                    'd' => {
+                        options.numeric = true;
+                        continue;
+                    }
+                    'l' => "lines",
+                    'b' => "bytes",
+                    'n' => "number",
+                    'a' => "suffix-length",
+                    _ => return Err(format!("invalid option -- '{flag}'")),
+                };
+                let attached = &arg[1 + at + 1..];
+                let value = if attached.is_empty() {
+                    iter.next()
+                        .cloned()
+                        .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?
+                } else {
+                    attached.to_string()
+                };
+                options.set(name, &value)?;
+                break;
+            }
+        }
+        let mut operands = operands.into_iter();
+        if let Some(input) = operands.next() {
+            options.input = input;
+        }
+        if let Some(prefix) = operands.next() {
+            options.prefix = prefix;
+        }
+        if let Some(extra) = operands.next() {
+            return Err(format!("extra operand '{extra}'"));
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let positive = |count: Option<usize>, what: &str| match count {
+            Some(count) if count > 0 => Ok(count),
+            _ => Err(format!("invalid number of {what}: '{value}'")),
+        };
+        match name {
+            "lines" => self.pieces = Pieces::Lines(positive(value.parse().ok(), "lines")?),
+            "bytes" => self.pieces = Pieces::Bytes(positive(parse_size(value), "bytes")?),
+            "number" => self.pieces = Pieces::Chunks(positive(value.parse().ok(), "chunks")?),
+            "suffix-length" => {
+                self.suffix_length = positive(value.parse().ok(), "suffix characters")?
+            }
+            _ => self.additional_suffix = value.to_string(),
+        }
+        Ok(())
     }
-    let file_arg = &args[idx];
-    if args.len() > idx + 1 { prefix = args[idx+1].clone(); }
-    let p = Path::new(file_arg).to_path_buf();
-    let pref = prefix.clone();
-    task::spawn_blocking(move || split_file(p, pref, size)).await??;
-    Ok(())
 }
 
-fn parse_size(s: &str) -> Result<u64> {
-    if let Some(rest) = s.strip_suffix('K') { return Ok(rest.parse::<u64>()? * 1024); }
-    if let Some(rest) = s.strip_suffix('M') { return Ok(rest.parse::<u64>()? * 1024*1024); }
-    Ok(s.parse::<u64>()?)
+/// SIZE as a byte count: digits with an optional K, M, G or T multiplier
+/// in powers of 1024 (also written KiB, ...), or KB, MB, GB, TB in powers
+/// of 1000
+fn parse_size(size: &str) -> Option<usize> {
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let count: usize = size[..digits].parse().ok()?;
+    let (unit, base) = match &size[digits..] {
+        "" => return Some(count),
+        "b" => return count.checked_mul(512),
+        unit if unit.len() == 3 && unit.ends_with("iB") => (&unit[..1], 1024usize),
+        unit if unit.len() == 2 && unit.ends_with('B') => (&unit[..1], 1000),
+        unit if unit.len() == 1 => (unit, 1024),
+        _ => return None,
+    };
+    let power = "KMGT".find(&unit.to_ascii_uppercase())? as u32 + 1;
+    count.checked_mul(base.checked_pow(power)?)
 }
 
-fn split_file(path: std::path::PathBuf, prefix: String, chunk_size: u64) -> Result<()> {
-    let mut infile = File::open(&path)?;
-    let mut buf = vec![0u8; chunk_size as usize];
-    let mut part = 0usize;
-    loop {
-        let n = infile.read(&mut buf)?;
-        if n == 0 { break; }
-        let suffix = encode_suffix(part);
-        let out_path = format!("{prefix}{suffix}");
-        let mut out = File::create(out_path)?;
-        out.write_all(&buf[..n])?;
-        part += 1;
+/// The suffix of the INDEXth piece, or None once LENGTH characters run out
+fn suffix(index: usize, length: usize, numeric: bool) -> Option<String> {
+    let (base, first) = if numeric { (10, b'0') } else { (26, b'a') };
+    let mut rest = index;
+    let mut digits = vec![first; length];
+    for digit in digits.iter_mut().rev() {
+        *digit = first + (rest % base) as u8;
+        rest /= base;
     }
-    Ok(())
+    (rest == 0).then(|| String::from_utf8(digits).unwrap_or_default())
 }
 
-fn encode_suffix(mut n: usize) -> String {
-    // Coreutils default: aa, ab ... az, ba, bb ... etc.
-    let mut chars = Vec::new();
-    loop {
-        chars.push(((n % 26) as u8 + b'a') as char);
-        n /= 26;
-        if n == 0 { break; }
-        n -= 1; // adjust
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("split: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let mut inputs = Inputs::new(cwd, stdin);
+    let data = match inputs.read(&options.input) {
+        Ok(data) => data,
+        Err(e) => {
+            outcome.stderr = format!("split: {}: {}\n", options.input, io_message(&e)).into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+
+    let pieces: Vec<&[u8]> = match options.pieces {
+        Pieces::Lines(count) => {
+            let mut pieces = Vec::new();
+            let mut start = 0;
+            let mut seen = 0;
+            for (at, _) in data.iter().enumerate().filter(|&(_, &byte)| byte == b'\n') {
+                seen += 1;
+                if seen == count {
+                    pieces.push(&data[start..=at]);
+                    start = at + 1;
+                    seen = 0;
+                }
+            }
+            if start < data.len() {
+                pieces.push(&data[start..]);
+            }
+            pieces
+        }
+        Pieces::Bytes(size) => data.chunks(size).collect(),
+        Pieces::Chunks(count) => {
+            let size = data.len() / count;
+            (0..count)
+                .map(|index| {
+                    let end = if index + 1 == count {
+                        data.len()
+                    } else {
+                        (index + 1) * size
+                    };
+                    &data[index * size..end]
+                })
+                .collect()
+        }
+    };
+
+    for (index, piece) in pieces.into_iter().enumerate() {
+        let Some(suffix) = suffix(index, options.suffix_length, options.numeric) else {
+            outcome
+                .stderr
+                .extend(b"split: output file suffixes exhausted\n");
+            outcome.status = 1;
+            break;
+        };
+        let name = format!("{}{suffix}{}", options.prefix, options.additional_suffix);
+        if options.verbose {
+            outcome
+                .stdout
+                .extend(format!("creating file '{name}'\n").as_bytes());
+        }
+        if let Err(e) = std::fs::write(inputs.path(&name), piece) {
+            outcome
+                .stderr
+                .extend(format!("split: {name}: {}\n", io_message(&e)).as_bytes());
+            outcome.status = 1;
+            break;
+        }
     }
-    chars.iter().rev().collect()
+    outcome
 }
 
 #[cfg(test)]
-mod tests { use super::*; use tempfile::NamedTempFile; use std::io::Write;
-    #[cfg(feature = "async-runtime")]
-    #[tokio::test]
-    async fn split_basic(){ let mut f=NamedTempFile::new().unwrap(); f.write_all(&vec![0u8;2000]).unwrap(); split_cli(&[f.path().to_string_lossy().into()]).await.unwrap(); }
-    #[cfg(not(feature = "async-runtime"))]
+mod tests {
+    use super::*;
+
+    fn split(args: &[&str], dir: &Path, input: &str) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        run(&args, dir, &mut input.as_bytes()).status
+    }
+
     #[test]
-    fn split_basic_sync(){ let mut f=NamedTempFile::new().unwrap(); f.write_all(&vec![0u8;2000]).unwrap(); split_cli(&[f.path().to_string_lossy().into()]).unwrap(); }
-} 
+    fn names_and_fills_pieces() {
+        let dir = tempfile::tempdir().unwrap();
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(split(&["-l", "2"], dir.path(), "1\n2\n3\n"), 0);
+        assert_eq!((read("xaa"), read("xab")), ("1\n2\n".into(), "3\n".into()));
+
+        assert_eq!(
+            split(&["-b3", "-d", "-", "part."], dir.path(), "abcdefg"),
+            0
+        );
+        assert_eq!(
+            read("part.00") + &read("part.01") + &read("part.02"),
+            "abcdefg"
+        );
+
+        assert_eq!(
+            split(&["-n", "2", "-a", "1", "-", "c"], dir.path(), "abcde"),
+            0
+        );
+        assert_eq!((read("ca"), read("cb")), ("ab".into(), "cde".into()));
+
+        assert_eq!(
+            split(&["-b1", "-a1", "-d", "-", "e"], dir.path(), "0123456789x"),
+            1
+        );
+    }
 
+    #[test]
+    fn parses_sizes_and_suffixes() {
+        assert_eq!(parse_size("10"), Some(10));
+        assert_eq!(parse_size("2K"), Some(2048));
+        assert_eq!(parse_size("1MB"), Some(1_000_000));
+        assert_eq!(parse_size("1KiB"), Some(1024));
+        assert_eq!(parse_size("1Q"), None);
+        assert_eq!(suffix(27, 2, false).as_deref(), Some("bb"));
+        assert_eq!(suffix(100, 2, true), None);
+    }
+}
@@ -1,48 +1,486 @@
-//! `ssh` builtin  Esecure shell client wrapper.
+//! `ssh` builtin - log in to another host or run a command there
 //!
-//! NexusShell intentionally leverages the platform-native OpenSSH client for
-//! full protocol compatibility, advanced crypto support, and decades of battle
-//!-tested reliability. When a compatible `ssh` executable is present in the
-//! `PATH`, we simply re-exec it, forwarding every command-line argument so that
-//! users can rely on 100% behavioural parity with their existing workflows.
+//! Syntax:
+//!   ssh [-nqTt] [-i FILE] [-l USER] [-o OPTION] [-p PORT] [USER@]HOST [COMMAND...]
 //!
-//! If the binary is not found, an error is returned suggesting installation
-//! instructions. Implementing a full SSH stack in Rust would be outside the
-//! immediate scope of NexusShell and would risk diverging from OpenSSH’s proven
-//! security record.
+//! Without a COMMAND ssh starts a login shell on HOST. When standard input
+//! is a terminal the shell gets a terminal of its own there, sized like the
+//! local one and resized along with it, and the local terminal passes every
+//! key on, Ctrl-C included, until the remote shell exits. With a COMMAND the
+//! words are joined with spaces and run by the remote user's shell, taking
+//! standard input and giving back its output and errors.
 //!
-//! Note: Windows users may have `ssh.exe` bundled with recent Win10/11 or via
-//! Git for Windows. macOS and most Linux distros ship `ssh` by default.
-
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
-
-/// Entry point for the `ssh` builtin.
-pub fn ssh_cli(args: &[String]) -> Result<()> {
-    // Candidate executable names in preferred order.
-    let candidates = if cfg!(windows) {
-        vec!["ssh.exe", "ssh"]
-    } else {
-        vec!["ssh"]
+//! The host's key is checked against `~/.ssh/known_hosts` first, and an
+//! unknown one is shown for the user to accept. Logging in tries the keys of
+//! the agent behind `SSH_AUTH_SOCK`, then the `-i` FILEs and `~/.ssh/id_*`,
+//! then asks for a password.
+//!
+//! Options:
+//!   -p PORT     connect to PORT instead of 22
+//!   -l USER     log in as USER
+//!   -i FILE     try the private key in FILE first
+//!   -o OPTION   set an OpenSSH option: StrictHostKeyChecking,
+//!               UserKnownHostsFile, IdentityFile, BatchMode,
+//!               ConnectTimeout, Port, User or LogLevel
+//!   -t          ask for a remote terminal even when running a COMMAND
+//!   -T          never ask for a remote terminal
+//!   -n          read nothing from standard input
+//!   -q          leave out warnings
+//!
+//! The exit status is that of the remote shell or COMMAND, and 255 when the
+//! connection or logging in failed.
+
+use crate::common::ssh::{self as session, Session};
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::terminal::{ResizeWatch, TerminalSize};
+use russh::ChannelMsg;
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How often the relay checks the terminal's size and the shell's time
+const TICK: Duration = Duration::from_millis(100);
+
+/// Bytes of input sent at a time
+const CHUNK: usize = 16 * 1024;
+
+/// The status for a failed connection or login
+const FAILED: i32 = 255;
+
+/// The size a remote terminal gets when the local one has none
+const FALLBACK_SIZE: TerminalSize = TerminalSize {
+    columns: 80,
+    rows: 24,
+};
+
+const USAGE: &str = "usage: ssh [-nqTt] [-i identity_file] [-l login_name] [-o option] \
+                     [-p port] destination [command ...]";
+
+/// The `ssh` builtin command implementation
+pub struct SshCommand;
+
+impl Builtin for SshCommand {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Log in to another host or run a command there"
+    }
+
+    fn description(&self) -> &'static str {
+        "Open an SSH session to HOST, checking its key against known_hosts and logging in \
+         with the agent, a key file or a password, and start a shell there or run COMMAND."
+    }
+
+    fn usage(&self) -> &'static str {
+        "ssh [-nqTt] [-i FILE] [-l USER] [-o OPTION] [-p PORT] [USER@]HOST [COMMAND...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Work on another host. Use 'ssh alice@example.com' for a shell there, \
+         'ssh -p 2222 host uptime' to run a command or \
+         'ssh -o BatchMode=yes host true' to check that a key gets you in."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(FAILED)
+                    .with_error(format!("{message}{USAGE}\n").into_bytes()))
+            }
+        };
+        let mut input = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+        let terminal = ctx.is_interactive() && io::stdin().is_terminal();
+        let term = ctx.get_var("TERM");
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let status = if ctx.is_interactive() && io::stdout().is_terminal() {
+            let mut out = std::mem::replace(&mut ctx.stdout, Box::new(io::sink()));
+            let mut err = std::mem::replace(&mut ctx.stderr, Box::new(io::sink()));
+            let status = run(
+                &options,
+                &mut Streams {
+                    input: &mut *input,
+                    terminal,
+                    term,
+                    output: &mut *out,
+                    errors: &mut *err,
+                    stopped: &|| ctx.is_timed_out(),
+                },
+            );
+            ctx.stdout = out;
+            ctx.stderr = err;
+            status
+        } else {
+            run(
+                &options,
+                &mut Streams {
+                    input: &mut *input,
+                    terminal,
+                    term,
+                    output: &mut stdout,
+                    errors: &mut stderr,
+                    stopped: &|| ctx.is_timed_out(),
+                },
+            )
+        };
+        ctx.stdin = input;
+        Ok(ExecutionResult::success(status)
+            .with_output(stdout)
+            .with_error(stderr))
+    }
+}
+
+/// Run ssh for the legacy dispatcher on the process's standard streams
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}{USAGE}");
+            return Ok(FAILED);
+        }
     };
+    Ok(run(
+        &options,
+        &mut Streams {
+            input: &mut io::stdin(),
+            terminal: io::stdin().is_terminal(),
+            term: std::env::var("TERM").ok(),
+            output: &mut io::stdout(),
+            errors: &mut io::stderr(),
+            stopped: &|| false,
+        },
+    ))
+}
+
+struct Streams<'a> {
+    input: &'a mut (dyn Read + Send),
+    /// Standard input is the user's terminal
+    terminal: bool,
+    /// The terminal type to give a remote terminal
+    term: Option<String>,
+    output: &'a mut dyn Write,
+    errors: &'a mut dyn Write,
+    stopped: &'a dyn Fn() -> bool,
+}
 
-    for bin in candidates {
-        if let Ok(path) = which(bin) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("ssh: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
+#[derive(Debug, PartialEq)]
+struct Options {
+    ssh: session::Options,
+    destination: String,
+    /// The words of the command, joined with spaces
+    command: Option<String>,
+    /// `-t` is true and `-T` false
+    want_terminal: Option<bool>,
+    no_input: bool,
+}
+
+impl Options {
+    /// Parse the arguments, with errors ending in a newline so that the
+    /// usage line can follow them
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            ssh: session::Options::default(),
+            destination: String::new(),
+            command: None,
+            want_terminal: None,
+            no_input: false,
+        };
+        let mut iter = args.iter();
+        let mut destination = None;
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                destination = iter.next();
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                destination = Some(arg);
+                break;
+            };
+            for (at, flag) in flags.char_indices() {
+                match flag {
+                    't' => options.want_terminal = Some(true),
+                    'T' => options.want_terminal = Some(false),
+                    'n' => options.no_input = true,
+                    'q' => options.ssh.quiet = true,
+                    'p' | 'l' | 'i' | 'o' => {
+                        let rest = &flags[at + 1..];
+                        let value = match rest.is_empty() {
+                            false => rest.to_string(),
+                            true => iter.next().cloned().ok_or_else(|| {
+                                format!("option requires an argument -- {flag}\n")
+                            })?,
+                        };
+                        match flag {
+                            'p' => {
+                                options.ssh.port = Some(
+                                    session::parse_port(&value).map_err(|e| format!("{e}\n"))?,
+                                )
+                            }
+                            'l' => options.ssh.user = Some(value),
+                            'i' => options.ssh.identities.push(value.into()),
+                            _ => options
+                                .ssh
+                                .set(&value)
+                                .map_err(|e| format!("command-line line 0: {e}\n"))?,
+                        }
+                        break;
+                    }
+                    _ => return Err(format!("unknown option -- {flag}\n")),
+                }
+            }
+        }
+        options.destination = destination.cloned().ok_or_else(String::new)?;
+        let words: Vec<&str> = iter.map(String::as_str).collect();
+        if !words.is_empty() {
+            options.command = Some(words.join(" "));
         }
+        Ok(options)
     }
 
-    Err(anyhow!("ssh: no compatible ssh client found in PATH; please install OpenSSH"))
+    /// Whether to ask for a remote terminal: for a shell on a terminal,
+    /// unless `-t` or `-T` says otherwise
+    fn pty(&self, terminal: bool) -> bool {
+        self.want_terminal
+            .unwrap_or(self.command.is_none() && terminal && !self.no_input)
+    }
 }
 
-pub fn execute(args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    match ssh_cli(args) {
-        Ok(()) => Ok(0),
-        Err(e) => Err(crate::common::BuiltinError::Other(e.to_string())),
+fn run(options: &Options, streams: &mut Streams) -> i32 {
+    let Streams {
+        input,
+        terminal,
+        term,
+        output,
+        errors,
+        stopped,
+    } = streams;
+    let target = match options.ssh.target(&options.destination) {
+        Ok(target) => target,
+        Err(message) => {
+            let _ = writeln!(errors, "ssh: {message}");
+            return FAILED;
+        }
+    };
+    let session = match Session::connect(&target, &options.ssh, *errors) {
+        Ok(session) => session,
+        Err(message) => {
+            let _ = writeln!(errors, "ssh: {message}");
+            return FAILED;
+        }
+    };
+    let pty = options
+        .pty(*terminal)
+        .then(|| term.as_deref().unwrap_or("xterm-256color"));
+    // The remote terminal handles keys, so the local one passes them on raw
+    let raw = pty.is_some() && *terminal && crossterm::terminal::enable_raw_mode().is_ok();
+    let done = AtomicBool::new(false);
+    let status = thread::scope(|scope| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        if options.no_input {
+            drop(sender);
+        } else {
+            let (terminal, done) = (*terminal, &done);
+            scope.spawn(move || feed(*input, terminal, &sender, done));
+        }
+        let status = session.block_on(relay(
+            &session,
+            options.command.as_deref(),
+            pty,
+            receiver,
+            *output,
+            *errors,
+            *stopped,
+        ));
+        done.store(true, Ordering::Relaxed);
+        status
+    });
+    if raw {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+    session.close();
+    match status {
+        Ok(status) => {
+            if pty.is_some() && !options.ssh.quiet {
+                let _ = writeln!(errors, "Connection to {} closed.", target.host);
+            }
+            status
+        }
+        Err(message) => {
+            let _ = writeln!(errors, "ssh: {message}");
+            FAILED
+        }
+    }
+}
+
+/// Pass input to the relay in chunks, and an empty one at its end
+fn feed(
+    input: &mut (dyn Read + Send),
+    terminal: bool,
+    sender: &mpsc::UnboundedSender<Vec<u8>>,
+    done: &AtomicBool,
+) {
+    let mut buf = vec![0; CHUNK];
+    loop {
+        if terminal && !crate::nc::readable(done) {
+            return;
+        }
+        let n = match input.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => 0,
+        };
+        if sender.send(buf[..n].to_vec()).is_err() || n == 0 {
+            return;
+        }
+    }
+}
+
+/// What woke the relay up
+enum Event {
+    Remote(Option<ChannelMsg>),
+    Input(Option<Vec<u8>>),
+    Tick,
+}
+
+/// Start the shell or command, on a remote terminal of the `pty` type when
+/// there is one, and copy input to it and its output back until it exits,
+/// returning its status
+async fn relay(
+    session: &Session,
+    command: Option<&str>,
+    pty: Option<&str>,
+    mut input: mpsc::UnboundedReceiver<Vec<u8>>,
+    output: &mut dyn Write,
+    errors: &mut dyn Write,
+    stopped: &dyn Fn() -> bool,
+) -> Result<i32, String> {
+    let failed = |e: russh::Error| e.to_string();
+    let mut channel = session.channel().await?;
+    let mut watch = ResizeWatch::new();
+    if let Some(term) = pty {
+        let size = watch.current().unwrap_or(FALLBACK_SIZE);
+        channel
+            .request_pty(
+                false,
+                term,
+                size.columns.into(),
+                size.rows.into(),
+                0,
+                0,
+                &[],
+            )
+            .await
+            .map_err(failed)?;
+    }
+    let started = match command {
+        Some(command) => channel.exec(true, command).await,
+        None => channel.request_shell(true).await,
+    };
+    started.map_err(failed)?;
+
+    let mut ticker = tokio::time::interval(TICK);
+    let mut reading = true;
+    let mut status = None;
+    loop {
+        let event = tokio::select! {
+            message = channel.wait() => Event::Remote(message),
+            chunk = input.recv(), if reading => Event::Input(chunk),
+            _ = ticker.tick() => Event::Tick,
+        };
+        match event {
+            Event::Remote(Some(ChannelMsg::Data { data })) => {
+                output.write_all(&data).map_err(|e| e.to_string())?;
+                let _ = output.flush();
+            }
+            Event::Remote(Some(ChannelMsg::ExtendedData { data, .. })) => {
+                let _ = errors.write_all(&data);
+                let _ = errors.flush();
+            }
+            Event::Remote(Some(ChannelMsg::ExitStatus { exit_status })) => {
+                status = Some(exit_status as i32);
+            }
+            Event::Remote(Some(ChannelMsg::ExitSignal { signal_name, .. })) => {
+                let _ = writeln!(
+                    errors,
+                    "ssh: remote command killed by signal {signal_name:?}"
+                );
+                status = Some(FAILED);
+            }
+            Event::Remote(Some(ChannelMsg::Close)) | Event::Remote(None) => break,
+            Event::Remote(Some(_)) => {}
+            Event::Input(Some(chunk)) if !chunk.is_empty() => {
+                channel.data(&chunk[..]).await.map_err(failed)?;
+            }
+            Event::Input(_) => {
+                reading = false;
+                channel.eof().await.map_err(failed)?;
+            }
+            Event::Tick => {
+                if stopped() {
+                    let _ = channel.close().await;
+                    return Ok(FAILED);
+                }
+                if let Some(size) = watch.changed().filter(|_| pty.is_some()) {
+                    let _ = channel
+                        .window_change(size.columns.into(), size.rows.into(), 0, 0)
+                        .await;
+                }
+            }
+        }
+    }
+    status.ok_or_else(|| "the connection closed without an exit status".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parses_options_destination_and_command() {
+        let parsed = options(&["-p", "2222", "-qi", "key", "alice@host", "ls", "-l"]).unwrap();
+        assert_eq!(parsed.ssh.port, Some(2222));
+        assert!(parsed.ssh.quiet);
+        assert_eq!(parsed.ssh.identities, vec![std::path::PathBuf::from("key")]);
+        assert_eq!(parsed.destination, "alice@host");
+        assert_eq!(parsed.command.as_deref(), Some("ls -l"));
+        assert!(!parsed.pty(true));
+
+        let parsed = options(&["-oBatchMode=yes", "-lbob", "host"]).unwrap();
+        assert!(parsed.ssh.batch);
+        assert_eq!(parsed.ssh.user.as_deref(), Some("bob"));
+        assert_eq!(parsed.command, None);
+        assert!(parsed.pty(true));
+        assert!(!parsed.pty(false));
+        assert!(options(&["-T", "host"]).map(|o| !o.pty(true)).unwrap());
+        assert!(options(&["-t", "host", "top"])
+            .map(|o| o.pty(false))
+            .unwrap());
+
+        assert_eq!(
+            options(&["-x", "host"]).unwrap_err(),
+            "unknown option -- x\n"
+        );
+        assert_eq!(
+            options(&["-p"]).unwrap_err(),
+            "option requires an argument -- p\n"
+        );
+        assert_eq!(options(&["-p", "x", "host"]).unwrap_err(), "Bad port 'x'\n");
+        assert_eq!(
+            options(&["-o", "Nope=1", "host"]).unwrap_err(),
+            "command-line line 0: unsupported option \"Nope\"\n"
+        );
+        assert_eq!(options(&["-q"]).unwrap_err(), "");
     }
 }
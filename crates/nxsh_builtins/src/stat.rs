@@ -35,6 +35,7 @@ struct StatOptions {
     dereference: bool,
     file_system: bool,
     terse: bool,
+    full: bool,
     format: Option<String>,
     printf_format: Option<String>,
     files: Vec<String>,
@@ -87,6 +88,9 @@ pub fn stat_cli(args: &[String]) -> anyhow::Result<()> {
             } else {
                 display_default_format(&file_info)?;
             }
+            if options.full {
+                display_security_details(&file_info, options.dereference)?;
+            }
         }
     }
 
@@ -110,6 +114,9 @@ fn parse_stat_args(args: &[String]) -> Result<StatOptions> {
             "-t" | "--terse" => {
                 options.terse = true;
             }
+            "--full" => {
+                options.full = true;
+            }
             "-c" | "--format" => {
                 if i + 1 >= args.len() {
                     return Err(anyhow!("Option {} requires an argument", arg));
@@ -292,6 +299,88 @@ fn display_terse_format(info: &FileInfo) -> Result<()> {
     Ok(())
 }
 
+/// The extended attributes, access control list and alternate data streams
+/// of a file, each section left out when the file or platform has none
+fn display_security_details(info: &FileInfo, follow: bool) -> Result<()> {
+    use nxsh_hal::fs::{acl, alternate_streams, get_xattr, list_xattrs, Acl, PosixAclTag};
+    use nxsh_hal::HalError;
+
+    let path = &info.path;
+    let unsupported = |e: &HalError| matches!(e, HalError::Unsupported(_));
+    let mut names = match list_xattrs(path, follow) {
+        Ok(names) => names,
+        Err(e) if unsupported(&e) => Vec::new(),
+        Err(e) => {
+            return Err(anyhow!(
+                "Cannot list attributes of {}: {}",
+                path.display(),
+                e
+            ))
+        }
+    };
+    // The ACL attributes are shown decoded below
+    names.retain(|name| !name.to_string_lossy().starts_with("system.posix_acl_"));
+    if !names.is_empty() {
+        names.sort();
+        println!("Extended attributes:");
+        for name in names {
+            let value = get_xattr(path, &name, follow)
+                .map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?
+                .unwrap_or_default();
+            println!(
+                "  {}={}",
+                name.to_string_lossy(),
+                crate::xattr::display_value(&value)
+            );
+        }
+    }
+
+    match acl(path).map_err(|e| anyhow!("Cannot read the ACL of {}: {}", path.display(), e))? {
+        Some(Acl::Posix { access, default }) => {
+            println!("Access control list:");
+            let entries = access
+                .iter()
+                .map(|entry| ("", entry))
+                .chain(default.iter().map(|entry| ("default:", entry)));
+            for (prefix, entry) in entries {
+                let qualifier = match (entry.tag, entry.id) {
+                    (PosixAclTag::User, Some(uid)) => get_username(uid),
+                    (PosixAclTag::Group, Some(gid)) => get_groupname(gid),
+                    _ => String::new(),
+                };
+                let tag = match entry.tag {
+                    PosixAclTag::UserObj | PosixAclTag::User => "user",
+                    PosixAclTag::GroupObj | PosixAclTag::Group => "group",
+                    PosixAclTag::Mask => "mask",
+                    PosixAclTag::Other => "other",
+                };
+                println!("  {prefix}{tag}:{qualifier}:{}", entry.permission_string());
+            }
+        }
+        Some(Acl::Windows { owner, entries }) => {
+            println!("Access control list:");
+            if let Some(owner) = owner {
+                println!("  owner: {owner}");
+            }
+            for entry in entries {
+                let kind = if entry.allow { "allow" } else { "deny" };
+                println!("  {kind} {}:{}", entry.trustee, entry.rights());
+            }
+        }
+        None => {}
+    }
+
+    let streams = alternate_streams(path)
+        .map_err(|e| anyhow!("Cannot list the streams of {}: {}", path.display(), e))?;
+    if !streams.is_empty() {
+        println!("Alternate data streams:");
+        for stream in streams {
+            println!("  {}  {} bytes", stream.name, stream.size);
+        }
+    }
+    Ok(())
+}
+
 fn display_filesystem_default(_info: &FilesystemInfo) -> Result<()> {
     // Simplified implementation
     println!("Filesystem information not fully implemented");
@@ -505,6 +594,7 @@ fn print_help() {
     println!("  -c  --format=FORMAT   use the specified FORMAT instead of the default");
     println!("      --printf=FORMAT   like --format, but interpret backslash escapes");
     println!("  -t, --terse           print the information in terse form");
+    println!("      --full            also print extended attributes, the ACL and data streams");
     println!("      --help            display this help and exit");
     println!("      --version         output version information and exit");
 }
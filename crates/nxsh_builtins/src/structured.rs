@@ -0,0 +1,1196 @@
+//! Structured pipeline commands
+//!
+//! `ls`, `ps`, `netstat`, `dig`, `df`, `mount`, `lsblk`, `du`, `stat` and `env` produce
+//! typed tables, `curl` the value or text it fetches, and `where`, `select`, `sort-by`, `group-by`, `get`,
+//! `update`, `flatten`, `first`, `last` and `length` work on them, when
+//! every command of a pipeline is one of these, or all but a first one
+//! whose output the conversions in [`crate::conversions`] read:
+//!
+//!   ls src | where size -gt 1000 | sort-by -r size | select name size
+//!   ps | group-by user --sum rss | sort-by rss_sum:desc
+//!   curl -s $url | from-json | get user.addresses.0.city
+//!
+//! The executor hands each stage's value to the next and draws the result
+//! with [`render_table`] when it goes to the terminal, or as tab separated
+//! lines for a file or command substitution. Alone, or next to any other
+//! command, these names run the ordinary text commands.
+//!
+//! `where` takes its operators in word form too (`-eq`, `-ne`, `-gt`, `-lt`,
+//! `-ge`, `-le`), since `>` and `<` would be read as redirections; the
+//! condition language is described in [`crate::query`].
+
+use crate::conversions::{child, element, KeyOrder};
+use crate::query::{
+    get_path, parse_path, parse_value, set_path, Aggregate, Condition, PathSegment, SortKey,
+};
+use crate::universal_formatter::UniversalFormatter;
+use chrono::{DateTime, Utc};
+use nxsh_core::context::ShellContext;
+use nxsh_core::error::{ErrorKind, RuntimeErrorKind};
+use nxsh_core::structured_commands::{FirstCommand, LastCommand, LengthCommand, SelectCommand};
+use nxsh_core::{
+    PipelineData, ShellError, ShellResult, StructuredBuiltin, StructuredCommand, StructuredValue,
+};
+use nxsh_hal::ProcessSnapshot;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+type Row = HashMap<String, StructuredValue>;
+
+/// Draw a structured pipeline's result for the terminal
+pub fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    UniversalFormatter::new()
+        .and_then(|formatter| formatter.format_table(headers, rows))
+        .unwrap_or_default()
+}
+
+/// `ls [-a] [PATH...]`: name, type, size and modification time of each entry
+pub struct LsTable;
+
+impl StructuredBuiltin for LsTable {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let mut all = false;
+        let mut paths = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-a" | "--all" => all = true,
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Err(invalid(format!("{f}: invalid option")))
+                }
+                _ => paths.push(arg.as_str()),
+            }
+        }
+        if paths.is_empty() {
+            paths.push(".");
+        }
+
+        let mut rows = Vec::new();
+        for &path in &paths {
+            let full = ctx.cwd.join(path);
+            let meta =
+                std::fs::symlink_metadata(&full).map_err(|e| invalid(format!("{path}: {e}")))?;
+            if !meta.is_dir() {
+                rows.push(file_row(path.to_string(), &meta));
+                continue;
+            }
+            let entries = std::fs::read_dir(&full).map_err(|e| invalid(format!("{path}: {e}")))?;
+            let mut listed: Vec<(String, Row)> = entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if !all && name.starts_with('.') {
+                        return None;
+                    }
+                    let meta = entry.path().symlink_metadata().ok()?;
+                    // With several arguments each name says where it was found
+                    let shown = if paths.len() > 1 {
+                        Path::new(path).join(&name).display().to_string()
+                    } else {
+                        name.clone()
+                    };
+                    Some((name, file_row(shown, &meta)))
+                })
+                .collect();
+            listed.sort_by(|a, b| a.0.cmp(&b.0));
+            rows.extend(listed.into_iter().map(|(_, row)| row));
+        }
+        Ok(table(rows, &["name", "type", "size", "modified"]))
+    }
+}
+
+/// `ps`: every process with its owner, state and resident memory
+pub struct PsTable;
+
+impl StructuredBuiltin for PsTable {
+    fn name(&self) -> &'static str {
+        "ps"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if let Some(arg) = args.first() {
+            return Err(invalid(format!("{arg}: unexpected argument")));
+        }
+        let snapshot = ProcessSnapshot::capture().map_err(|e| invalid(e.to_string()))?;
+        let rows = snapshot
+            .into_processes()
+            .into_iter()
+            .map(|process| {
+                Row::from([
+                    ("pid".to_string(), StructuredValue::Int(process.pid.into())),
+                    (
+                        "ppid".to_string(),
+                        StructuredValue::Int(process.ppid.into()),
+                    ),
+                    ("user".to_string(), StructuredValue::String(process.user)),
+                    (
+                        "state".to_string(),
+                        StructuredValue::String(process.state.code().to_string()),
+                    ),
+                    ("rss".to_string(), int(process.rss)),
+                    (
+                        "command".to_string(),
+                        StructuredValue::String(process.command_line),
+                    ),
+                ])
+            })
+            .collect();
+        Ok(table(
+            rows,
+            &["pid", "ppid", "user", "state", "rss", "command"],
+        ))
+    }
+}
+
+/// `netstat [-atul46]`: the sockets `netstat` would list, with their
+/// addresses and owners
+pub struct NetstatTable;
+
+impl StructuredBuiltin for NetstatTable {
+    fn name(&self) -> &'static str {
+        "netstat"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let sockets = crate::netstat::selected_sockets(args).map_err(invalid)?;
+        let optional = |value: Option<StructuredValue>| value.unwrap_or(StructuredValue::Nothing);
+        let rows = sockets
+            .iter()
+            .map(|socket| {
+                Row::from([
+                    (
+                        "proto".to_string(),
+                        StructuredValue::String(crate::netstat::protocol_name(socket)),
+                    ),
+                    (
+                        "local_address".to_string(),
+                        StructuredValue::String(socket.local.ip().to_string()),
+                    ),
+                    ("local_port".to_string(), int(socket.local.port().into())),
+                    (
+                        "remote_address".to_string(),
+                        optional(
+                            socket
+                                .remote
+                                .map(|remote| StructuredValue::String(remote.ip().to_string())),
+                        ),
+                    ),
+                    (
+                        "remote_port".to_string(),
+                        optional(socket.remote.map(|remote| int(remote.port().into()))),
+                    ),
+                    (
+                        "state".to_string(),
+                        StructuredValue::String(socket.state.name().to_string()),
+                    ),
+                    (
+                        "pid".to_string(),
+                        optional(socket.pid.map(|pid| StructuredValue::Int(pid.into()))),
+                    ),
+                    (
+                        "program".to_string(),
+                        optional(socket.program.clone().map(StructuredValue::String)),
+                    ),
+                ])
+            })
+            .collect();
+        Ok(table(
+            rows,
+            &[
+                "proto",
+                "local_address",
+                "local_port",
+                "remote_address",
+                "remote_port",
+                "state",
+                "pid",
+                "program",
+            ],
+        ))
+    }
+}
+
+/// `dig [@SERVER] [NAME] [TYPE]`: the records of the answer `dig` would
+/// print
+pub struct DigTable;
+
+impl StructuredBuiltin for DigTable {
+    fn name(&self) -> &'static str {
+        "dig"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let records = crate::dig::answer_records(args).map_err(invalid)?;
+        let rows = records
+            .into_iter()
+            .map(|record| {
+                Row::from([
+                    ("name".to_string(), StructuredValue::String(record.name)),
+                    (
+                        "type".to_string(),
+                        StructuredValue::String(record.rtype.to_string()),
+                    ),
+                    ("ttl".to_string(), int(record.ttl.into())),
+                    (
+                        "data".to_string(),
+                        StructuredValue::String(record.data.to_string()),
+                    ),
+                ])
+            })
+            .collect();
+        Ok(table(rows, &["name", "type", "ttl", "data"]))
+    }
+}
+
+/// `curl [OPTIONS] URL`: the reply's body, as the value it holds with
+/// `--json` or as text for a conversion
+pub struct CurlReply;
+
+impl StructuredBuiltin for CurlReply {
+    fn name(&self) -> &'static str {
+        "curl"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let (body, json) = crate::curl::reply_body(ctx, args).map_err(invalid)?;
+        if json {
+            return crate::conversions::json_data(&body);
+        }
+        Ok(PipelineData::new(StructuredValue::String(
+            String::from_utf8_lossy(&body).into_owned(),
+        )))
+    }
+}
+
+/// `df [-a] [-t TYPE] [-x TYPE] [FILE...]`: size, free space and inodes of
+/// the file systems `df` would list
+pub struct DfTable;
+
+impl StructuredBuiltin for DfTable {
+    fn name(&self) -> &'static str {
+        "df"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let filesystems = crate::df::selected_filesystems(args, &ctx.cwd).map_err(invalid)?;
+        let optional = |value: Option<u64>| value.map_or(StructuredValue::Nothing, int);
+        let rows = filesystems
+            .into_iter()
+            .map(|crate::df::Filesystem { mount, usage, .. }| {
+                Row::from([
+                    (
+                        "filesystem".to_string(),
+                        StructuredValue::String(mount.source),
+                    ),
+                    ("type".to_string(), StructuredValue::String(mount.fs_type)),
+                    (
+                        "mount".to_string(),
+                        StructuredValue::Path(mount.mount_point),
+                    ),
+                    ("size".to_string(), int(usage.total)),
+                    ("used".to_string(), int(usage.used())),
+                    ("available".to_string(), int(usage.available)),
+                    ("use%".to_string(), optional(usage.use_percent())),
+                    ("inodes".to_string(), optional(usage.files)),
+                    (
+                        "iused".to_string(),
+                        optional(
+                            usage
+                                .files
+                                .zip(usage.files_free)
+                                .map(|(files, free)| files.saturating_sub(free)),
+                        ),
+                    ),
+                    ("ifree".to_string(), optional(usage.files_free)),
+                    ("iuse%".to_string(), optional(usage.inode_use_percent())),
+                ])
+            })
+            .collect();
+        Ok(table(
+            rows,
+            &[
+                "filesystem",
+                "type",
+                "mount",
+                "size",
+                "used",
+                "available",
+                "use%",
+                "inodes",
+                "iused",
+                "ifree",
+                "iuse%",
+            ],
+        ))
+    }
+}
+
+/// `mount [-a] [-t TYPES]`: the mounted file systems with their options
+/// and devices
+pub struct MountTable;
+
+impl StructuredBuiltin for MountTable {
+    fn name(&self) -> &'static str {
+        "mount"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let mounts = crate::mount::selected_mounts(args).map_err(invalid)?;
+        let optional =
+            |value: Option<String>| value.map_or(StructuredValue::Nothing, StructuredValue::String);
+        let rows = mounts
+            .into_iter()
+            .map(|mount| {
+                Row::from([
+                    ("source".to_string(), StructuredValue::String(mount.device)),
+                    (
+                        "mount".to_string(),
+                        StructuredValue::Path(PathBuf::from(mount.mount_point)),
+                    ),
+                    (
+                        "type".to_string(),
+                        StructuredValue::String(mount.filesystem),
+                    ),
+                    (
+                        "options".to_string(),
+                        StructuredValue::String(mount.options.join(",")),
+                    ),
+                    ("device".to_string(), optional(mount.major_minor)),
+                    ("uuid".to_string(), optional(mount.uuid)),
+                    ("label".to_string(), optional(mount.label)),
+                ])
+            })
+            .collect();
+        Ok(table(
+            rows,
+            &[
+                "source", "mount", "type", "options", "device", "uuid", "label",
+            ],
+        ))
+    }
+}
+
+/// `lsblk [-ad] [DEVICE...]`: the block devices `lsblk` would list, with
+/// their file systems and mount points
+pub struct LsblkTable;
+
+impl StructuredBuiltin for LsblkTable {
+    fn name(&self) -> &'static str {
+        "lsblk"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let devices = crate::lsblk::selected_devices(args).map_err(invalid)?;
+        let optional =
+            |value: Option<String>| value.map_or(StructuredValue::Nothing, StructuredValue::String);
+        let rows = devices
+            .into_iter()
+            .map(|device| {
+                Row::from([
+                    ("name".to_string(), StructuredValue::String(device.name)),
+                    ("parent".to_string(), optional(device.parent)),
+                    (
+                        "type".to_string(),
+                        StructuredValue::String(device.kind.name().to_string()),
+                    ),
+                    ("size".to_string(), int(device.size)),
+                    (
+                        "removable".to_string(),
+                        StructuredValue::Bool(device.removable),
+                    ),
+                    (
+                        "read_only".to_string(),
+                        StructuredValue::Bool(device.read_only),
+                    ),
+                    ("fstype".to_string(), optional(device.fs_type)),
+                    ("label".to_string(), optional(device.label)),
+                    ("uuid".to_string(), optional(device.uuid)),
+                    (
+                        "mountpoints".to_string(),
+                        StructuredValue::List(
+                            device
+                                .mount_points
+                                .into_iter()
+                                .map(StructuredValue::Path)
+                                .collect(),
+                        ),
+                    ),
+                ])
+            })
+            .collect();
+        Ok(table(
+            rows,
+            &[
+                "name",
+                "parent",
+                "type",
+                "size",
+                "removable",
+                "read_only",
+                "fstype",
+                "label",
+                "uuid",
+                "mountpoints",
+            ],
+        ))
+    }
+}
+
+/// `du [-as] [-d DEPTH] [-t SIZE] [FILE...]`: the space of each entry `du`
+/// would list, in bytes
+pub struct DuTable;
+
+impl StructuredBuiltin for DuTable {
+    fn name(&self) -> &'static str {
+        "du"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let entries = crate::du::selected_usage(args, &ctx.cwd).map_err(invalid)?;
+        let rows = entries
+            .into_iter()
+            .map(|entry| {
+                Row::from([
+                    ("path".to_string(), StructuredValue::Path(entry.path)),
+                    ("size".to_string(), int(entry.size)),
+                    ("apparent".to_string(), int(entry.apparent)),
+                    ("disk".to_string(), int(entry.disk)),
+                    ("files".to_string(), int(entry.files)),
+                ])
+            })
+            .collect();
+        Ok(table(rows, &["path", "size", "apparent", "disk", "files"]))
+    }
+}
+
+/// `trash list`: what is in the trash, oldest first
+pub struct TrashTable;
+
+impl StructuredBuiltin for TrashTable {
+    fn name(&self) -> &'static str {
+        "trash"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let items = crate::trash::selected_items(args).map_err(invalid)?;
+        let rows = items
+            .into_iter()
+            .map(|item| {
+                let kind = if item.is_dir { "dir" } else { "file" };
+                Row::from([
+                    ("name".to_string(), StructuredValue::String(item.id)),
+                    (
+                        "path".to_string(),
+                        item.original_path
+                            .map_or(StructuredValue::Nothing, StructuredValue::Path),
+                    ),
+                    (
+                        "deleted".to_string(),
+                        item.deleted.map_or(StructuredValue::Nothing, |time| {
+                            StructuredValue::Date(DateTime::<Utc>::from(time))
+                        }),
+                    ),
+                    (
+                        "type".to_string(),
+                        StructuredValue::String(kind.to_string()),
+                    ),
+                ])
+            })
+            .collect();
+        Ok(table(rows, &["name", "path", "deleted", "type"]))
+    }
+}
+
+/// `stat [-L] PATH...`: one row of file attributes per PATH
+pub struct StatTable;
+
+impl StructuredBuiltin for StatTable {
+    fn name(&self) -> &'static str {
+        "stat"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let mut dereference = false;
+        let mut paths = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-L" | "--dereference" => dereference = true,
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Err(invalid(format!("{f}: invalid option")))
+                }
+                _ => paths.push(arg),
+            }
+        }
+        if paths.is_empty() {
+            return Err(invalid("missing operand"));
+        }
+
+        let mut rows = Vec::new();
+        for path in paths {
+            let full = ctx.cwd.join(path);
+            let meta = if dereference {
+                std::fs::metadata(&full)
+            } else {
+                std::fs::symlink_metadata(&full)
+            }
+            .map_err(|e| invalid(format!("{path}: {e}")))?;
+
+            let mut row = file_row(path.clone(), &meta);
+            row.insert("accessed".to_string(), time(meta.accessed()));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                row.insert(
+                    "mode".to_string(),
+                    StructuredValue::String(format!("{:04o}", meta.mode() & 0o7777)),
+                );
+                row.insert("links".to_string(), int(meta.nlink()));
+                row.insert("inode".to_string(), int(meta.ino()));
+                row.insert("uid".to_string(), int(meta.uid().into()));
+                row.insert("gid".to_string(), int(meta.gid().into()));
+            }
+            #[cfg(not(unix))]
+            row.insert(
+                "readonly".to_string(),
+                StructuredValue::Bool(meta.permissions().readonly()),
+            );
+            rows.push(row);
+        }
+        Ok(table(
+            rows,
+            &[
+                "name", "type", "size", "mode", "links", "inode", "uid", "gid", "accessed",
+                "modified",
+            ],
+        ))
+    }
+}
+
+/// `env [-i] [-u NAME]... [NAME=VALUE]...`: the variables `env` would print,
+/// by name
+pub struct EnvTable;
+
+impl StructuredBuiltin for EnvTable {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn run(
+        &self,
+        ctx: &mut ShellContext,
+        args: &[String],
+        _input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let vars = crate::env::listed_variables(ctx, args).map_err(invalid)?;
+        let rows = vars
+            .into_iter()
+            .map(|(name, value)| {
+                Row::from([
+                    ("name".to_string(), StructuredValue::String(name)),
+                    ("value".to_string(), StructuredValue::String(value)),
+                ])
+            })
+            .collect();
+        Ok(table(rows, &["name", "value"]))
+    }
+}
+
+/// `where CONDITION`: keep the rows CONDITION holds for (see [`crate::query`])
+pub struct WhereFilter;
+
+impl StructuredBuiltin for WhereFilter {
+    fn name(&self) -> &'static str {
+        "where"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let condition = Condition::parse(args).map_err(invalid)?;
+        let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let value = match input.value {
+            StructuredValue::Table(mut rows) => {
+                rows.retain(|row| condition.matches(row));
+                StructuredValue::Table(rows)
+            }
+            StructuredValue::List(mut items) => {
+                items.retain(
+                    |item| matches!(item, StructuredValue::Record(row) if condition.matches(row)),
+                );
+                StructuredValue::List(items)
+            }
+            StructuredValue::Record(row) if condition.matches(&row) => StructuredValue::Record(row),
+            StructuredValue::Record(_) => StructuredValue::Table(Vec::new()),
+            other => {
+                return Err(invalid(format!(
+                    "expects a table, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        Ok(PipelineData::new(value))
+    }
+}
+
+/// `select COLUMN...`: keep only the named columns, in that order
+pub struct SelectColumns;
+
+impl StructuredBuiltin for SelectColumns {
+    fn name(&self) -> &'static str {
+        "select"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        if args.is_empty() {
+            return Err(invalid("usage: select COLUMN..."));
+        }
+        let select = SelectCommand {
+            columns: args.to_vec(),
+        };
+        Ok(apply(&select, input)?.with_columns(args))
+    }
+}
+
+/// `sort-by [-r] KEY...`: order rows by each KEY in turn, numerically where
+/// the column holds numbers; a KEY is `COLUMN` or `COLUMN:desc`
+pub struct SortBy;
+
+impl StructuredBuiltin for SortBy {
+    fn name(&self) -> &'static str {
+        "sort-by"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let (reverse, keys) = match args {
+            [flag, keys @ ..] if flag == "-r" || flag == "--reverse" => (true, keys),
+            keys => (false, keys),
+        };
+        if keys.is_empty() {
+            return Err(invalid("usage: sort-by [-r] COLUMN[:desc]..."));
+        }
+        let keys = keys
+            .iter()
+            .map(|key| SortKey::parse(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let StructuredValue::Table(mut rows) = input.value else {
+            return Err(invalid(format!(
+                "expects a table, got {}",
+                input.value.type_name()
+            )));
+        };
+        rows.sort_by(|a, b| {
+            let ordering = SortKey::compare(&keys, a, b);
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        Ok(PipelineData::new(StructuredValue::Table(rows)))
+    }
+}
+
+/// `group-by COLUMN... [--sum|--min|--max|--avg COLUMN]...`: one row per
+/// distinct value of the COLUMNs, with its `count` and the aggregates asked for
+pub struct GroupBy;
+
+impl StructuredBuiltin for GroupBy {
+    fn name(&self) -> &'static str {
+        "group-by"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let mut columns = Vec::new();
+        let mut aggregates = Vec::new();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            if let Some(aggregate) = Aggregate::from_flag(arg) {
+                let column = rest
+                    .next()
+                    .ok_or_else(|| invalid(format!("{arg}: expects a column")))?;
+                aggregates.push((aggregate, column.clone()));
+            } else if arg == "--count" {
+                // Every group has its count; accepted for symmetry
+            } else if arg.starts_with("--") {
+                return Err(invalid(format!("{arg}: invalid option")));
+            } else {
+                columns.push(arg.clone());
+            }
+        }
+        if columns.is_empty() {
+            return Err(invalid(
+                "usage: group-by COLUMN... [--sum|--min|--max|--avg COLUMN]...",
+            ));
+        }
+        let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let StructuredValue::Table(rows) = input.value else {
+            return Err(invalid(format!(
+                "expects a table, got {}",
+                input.value.type_name()
+            )));
+        };
+
+        // Groups in the order their first row appears
+        let mut groups: Vec<(Vec<String>, Vec<Row>)> = Vec::new();
+        for row in rows {
+            let key: Vec<String> = columns
+                .iter()
+                .map(|column| row.get(column).map(ToString::to_string).unwrap_or_default())
+                .collect();
+            match groups.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, members)) => members.push(row),
+                None => groups.push((key, vec![row])),
+            }
+        }
+
+        let mut headers = columns.clone();
+        headers.push("count".to_string());
+        headers.extend(
+            aggregates
+                .iter()
+                .map(|(aggregate, column)| aggregate.column_name(column)),
+        );
+        let table = groups
+            .into_iter()
+            .map(|(_, members)| {
+                let mut out: Row = columns
+                    .iter()
+                    .map(|column| {
+                        let value = members[0].get(column).cloned();
+                        (column.clone(), value.unwrap_or(StructuredValue::Nothing))
+                    })
+                    .collect();
+                out.insert(
+                    "count".to_string(),
+                    StructuredValue::Int(members.len() as i64),
+                );
+                for (aggregate, column) in &aggregates {
+                    out.insert(
+                        aggregate.column_name(column),
+                        aggregate.compute(column, &members),
+                    );
+                }
+                out
+            })
+            .collect();
+        Ok(PipelineData::new(StructuredValue::Table(table)).with_columns(&headers))
+    }
+}
+
+/// `first [N]`: the first N rows (default 1)
+pub struct First;
+
+impl StructuredBuiltin for First {
+    fn name(&self) -> &'static str {
+        "first"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let count = count_arg(args)?;
+        apply(&FirstCommand { count }, input)
+    }
+}
+
+/// `last [N]`: the last N rows (default 1)
+pub struct Last;
+
+impl StructuredBuiltin for Last {
+    fn name(&self) -> &'static str {
+        "last"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let count = count_arg(args)?;
+        apply(&LastCommand { count }, input)
+    }
+}
+
+/// `get [-i] PATH`: the value at PATH (see [`crate::query`]); with `-i` a
+/// PATH that leads nowhere gives null rather than an error
+pub struct Get;
+
+impl StructuredBuiltin for Get {
+    fn name(&self) -> &'static str {
+        "get"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let (ignore_errors, path) = match args {
+            [flag, path] if flag == "-i" || flag == "--ignore-errors" => (true, path),
+            [path] => (false, path),
+            _ => return Err(invalid("usage: get [-i] PATH")),
+        };
+        let path = parse_path(path).map_err(invalid)?;
+        let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let value = match get_path(&input.value, &path) {
+            Ok(value) => value,
+            Err(_) if ignore_errors => StructuredValue::Nothing,
+            Err(message) => return Err(invalid(message)),
+        };
+
+        // An object picked out keeps the key order it was read with
+        let key_path = path
+            .iter()
+            .fold(String::new(), |key_path, segment| match segment {
+                PathSegment::Field(name) => child(&key_path, name),
+                PathSegment::Index(_) => element(&key_path),
+            });
+        let order = KeyOrder::from_data(&input).subtree(&key_path);
+        let columns = match &value {
+            StructuredValue::Record(_) => order.columns("").map(<[String]>::to_vec),
+            StructuredValue::Table(_) => order.columns(&element("")).map(<[String]>::to_vec),
+            _ => None,
+        };
+        let output = order.attach(PipelineData::new(value));
+        Ok(match columns {
+            Some(columns) => output.with_columns(&columns),
+            None => output,
+        })
+    }
+}
+
+/// `update PATH VALUE`: set the value at PATH, adding the field when it is
+/// missing; a field of a table is set in every row
+pub struct Update;
+
+impl StructuredBuiltin for Update {
+    fn name(&self) -> &'static str {
+        "update"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let [path, value @ ..] = args else {
+            return Err(invalid("usage: update PATH VALUE"));
+        };
+        if value.is_empty() {
+            return Err(invalid("usage: update PATH VALUE"));
+        }
+        let path = parse_path(path).map_err(invalid)?;
+        let mut data = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        set_path(&mut data.value, &path, &parse_value(&value.join(" "))).map_err(invalid)?;
+        Ok(data)
+    }
+}
+
+/// `flatten [COLUMN...]`: spread the fields of nested records into columns
+/// named `outer.inner`, and repeat a row for each item of a nested list or
+/// table; without COLUMNs every column is flattened
+pub struct Flatten;
+
+impl StructuredBuiltin for Flatten {
+    fn name(&self) -> &'static str {
+        "flatten"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+        let order = KeyOrder::from_data(&input);
+        // Where the rows' key order is kept: a record is its own one row
+        let row_path = match input.value {
+            StructuredValue::Record(_) => String::new(),
+            _ => element(""),
+        };
+        let rows = match input.value {
+            StructuredValue::Table(rows) => rows,
+            StructuredValue::Record(fields) => vec![fields],
+            StructuredValue::List(items)
+                if items
+                    .iter()
+                    .all(|item| matches!(item, StructuredValue::Record(_))) =>
+            {
+                items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        StructuredValue::Record(fields) => Some(fields),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            // A list of lists loses one level of nesting
+            StructuredValue::List(items) => {
+                let items = items
+                    .into_iter()
+                    .flat_map(|item| match item {
+                        StructuredValue::List(inner) => inner,
+                        StructuredValue::Table(rows) => {
+                            rows.into_iter().map(StructuredValue::Record).collect()
+                        }
+                        item => vec![item],
+                    })
+                    .collect();
+                return Ok(PipelineData::new(StructuredValue::List(items)));
+            }
+            other => {
+                return Err(invalid(format!(
+                    "expects a table, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut table = Vec::new();
+        for row in rows {
+            let mut expanded = vec![Row::new()];
+            for key in order.keys(&row_path, &row) {
+                let path = child(&row_path, key);
+                let value = &row[key];
+                let choices = match value {
+                    value if !args.is_empty() && !args.contains(key) => {
+                        vec![vec![(key.clone(), value.clone())]]
+                    }
+                    StructuredValue::List(items) if !items.is_empty() => items
+                        .iter()
+                        .map(|item| flatten_value(key, item, &element(&path), &order))
+                        .collect(),
+                    StructuredValue::Table(rows) if !rows.is_empty() => rows
+                        .iter()
+                        .map(|row| flatten_row(key, row, &element(&path), &order))
+                        .collect(),
+                    StructuredValue::List(_) | StructuredValue::Table(_) => {
+                        vec![vec![(key.clone(), StructuredValue::Nothing)]]
+                    }
+                    value => vec![flatten_value(key, value, &path, &order)],
+                };
+                for (name, _) in choices.iter().flatten() {
+                    if !columns.contains(name) {
+                        columns.push(name.clone());
+                    }
+                }
+                expanded = product(expanded, choices);
+            }
+            table.extend(expanded);
+        }
+        Ok(PipelineData::new(StructuredValue::Table(table)).with_columns(&columns))
+    }
+}
+
+/// The fields `value` under `name` becomes: one, or one per nested field
+fn flatten_value(
+    name: &str,
+    value: &StructuredValue,
+    path: &str,
+    order: &KeyOrder,
+) -> Vec<(String, StructuredValue)> {
+    match value {
+        StructuredValue::Record(fields) if !fields.is_empty() => {
+            flatten_row(name, fields, path, order)
+        }
+        value => vec![(name.to_string(), value.clone())],
+    }
+}
+
+fn flatten_row(
+    name: &str,
+    fields: &Row,
+    path: &str,
+    order: &KeyOrder,
+) -> Vec<(String, StructuredValue)> {
+    order
+        .keys(path, fields)
+        .into_iter()
+        .flat_map(|key| {
+            flatten_value(
+                &format!("{name}.{key}"),
+                &fields[key],
+                &child(path, key),
+                order,
+            )
+        })
+        .collect()
+}
+
+/// Every row of `rows` extended by every one of `choices`
+fn product(rows: Vec<Row>, choices: Vec<Vec<(String, StructuredValue)>>) -> Vec<Row> {
+    rows.iter()
+        .flat_map(|row| {
+            choices.iter().map(move |fields| {
+                let mut row = row.clone();
+                row.extend(fields.iter().cloned());
+                row
+            })
+        })
+        .collect()
+}
+
+/// `length`: the number of rows
+pub struct Length;
+
+impl StructuredBuiltin for Length {
+    fn name(&self) -> &'static str {
+        "length"
+    }
+
+    fn run(
+        &self,
+        _ctx: &mut ShellContext,
+        _args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData> {
+        apply(&LengthCommand, input)
+    }
+}
+
+/// Run a data command on the previous stage's value
+fn apply(
+    command: &dyn StructuredCommand,
+    input: Option<PipelineData>,
+) -> ShellResult<PipelineData> {
+    let input = input.ok_or_else(|| invalid("expects input from a structured command"))?;
+    command.process(input).map_err(|e| invalid(e.to_string()))
+}
+
+fn count_arg(args: &[String]) -> ShellResult<usize> {
+    match args {
+        [] => Ok(1),
+        [count] => count
+            .parse()
+            .map_err(|_| invalid(format!("{count}: invalid count"))),
+        _ => Err(invalid("too many arguments")),
+    }
+}
+
+fn file_row(name: String, meta: &Metadata) -> Row {
+    let kind = if meta.file_type().is_symlink() {
+        "symlink"
+    } else if meta.is_dir() {
+        "dir"
+    } else if meta.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+    Row::from([
+        ("name".to_string(), StructuredValue::String(name)),
+        (
+            "type".to_string(),
+            StructuredValue::String(kind.to_string()),
+        ),
+        ("size".to_string(), int(meta.len())),
+        ("modified".to_string(), time(meta.modified())),
+    ])
+}
+
+fn time(time: std::io::Result<std::time::SystemTime>) -> StructuredValue {
+    time.map(|time| StructuredValue::Date(DateTime::<Utc>::from(time)))
+        .unwrap_or(StructuredValue::Nothing)
+}
+
+fn int(value: u64) -> StructuredValue {
+    StructuredValue::Int(i64::try_from(value).unwrap_or(i64::MAX))
+}
+
+fn table(rows: Vec<Row>, columns: &[&str]) -> PipelineData {
+    PipelineData::new(StructuredValue::Table(rows)).with_columns(columns)
+}
+
+fn invalid(message: impl Into<String>) -> ShellError {
+    ShellError::new(
+        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
+        message,
+    )
+}
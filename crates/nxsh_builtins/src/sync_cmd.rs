@@ -1,39 +1,1304 @@
-//! `sync` command  Eflush file system buffers to disk.
-//! Usage: sync
-//! Unix: calls libc::sync(); Windows: FlushFileBuffers on all volumes.
+//! `sync` builtin - copy directory trees incrementally
+//!
+//! Syntax:
+//!   sync [-acnuv] [--delete] [--size-only] [--exclude PATTERN]
+//!        [--include PATTERN] [-e COMMAND] SRC... DEST
+//!   rcp [OPTION]... SRC... DEST
+//!   sync
+//!
+//! Copies each SRC to DEST the way rsync does, skipping the files that are
+//! already there: a file is copied again only when its size or modification
+//! time differs, or with `-c` when its content does. A SRC ending in `/`
+//! copies the directory's contents into DEST; without it the directory
+//! itself is copied and lands inside DEST. Files keep their permissions and
+//! times, symbolic links are copied as links and each file is written under
+//! a temporary name first, so that an interrupted run never leaves half a
+//! file behind.
+//!
+//! Options:
+//!   -n, --dry-run       only report what would be copied and deleted
+//!   -v, --verbose       list what is copied and deleted, and sum it up
+//!   -c, --checksum      compare the content of files of the same size
+//!                       instead of their times
+//!       --size-only     compare only the sizes of files
+//!   -u, --update        leave alone files that are newer in DEST
+//!       --delete        delete what DEST has and SRC does not
+//!       --exclude PATTERN  leave out what PATTERN matches
+//!       --include PATTERN  keep what PATTERN matches
+//!   -e, --rsh COMMAND   reach other hosts with COMMAND instead of `ssh`
+//!
+//! `-a` and `-r` are taken for rsync's sake and change nothing: sync always
+//! descends into directories and keeps times and permissions.
+//!
+//! The first `--exclude` or `--include` PATTERN matching a path decides
+//! whether it takes part, and paths none of them match do. A PATTERN is a
+//! wildcard matched against names, or against the path from the top of the
+//! transfer when it holds a `/`; a leading `/` anchors it at the top and a
+//! trailing one makes it match only directories. What an excluded directory
+//! holds is left out with it, and `--delete` never deletes what is excluded.
+//!
+//! In a build with the `remote` feature either SRC or DEST may be on another
+//! host, written `[USER@]HOST:PATH`, reached with `ssh` or the `-e` COMMAND.
+//! The host needs GNU `find`, `sha256sum` and a POSIX shell.
+//!
+//! `rcp` is the same command. Without operands `sync` instead flushes the
+//! file system buffers to disk, as the classic Unix command does.
+//!
+//! The exit status is 0 on success, 1 for bad usage, 20 when the shell's
+//! time ran out and 23 when some files could not be copied or deleted.
 
-use anyhow::Result;
+#[cfg(feature = "remote")]
+use crate::common::ssh::quote;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
-pub async fn sync_cli(_args: &[String]) -> Result<()> {
+/// The status for files that could not be copied or deleted
+const PARTIAL: i32 = 23;
+
+/// The status for a run cut short
+const STOPPED: i32 = 20;
+
+/// The `sync` builtin command implementation
+pub struct SyncCommand;
+
+/// The `rcp` builtin command implementation
+pub struct RcpCommand;
+
+impl Builtin for SyncCommand {
+    fn name(&self) -> &'static str {
+        "sync"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Copy directory trees incrementally"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy files and directory trees, skipping what is already up to date by size and \
+         time or by checksum, optionally deleting extra files, filtered by include and \
+         exclude patterns. Without operands, flush file system buffers."
+    }
+
+    fn usage(&self) -> &'static str {
+        "sync [-acnuv] [--delete] [--size-only] [--exclude PATTERN] [--include PATTERN] \
+         [-e COMMAND] SRC... DEST\n\
+         sync"
+    }
+
+    fn help(&self) -> &'static str {
+        "Mirror directory trees. Use 'sync -av src/ backup/' to copy what changed, \
+         'sync -n --delete src/ backup/' to preview deletions or \
+         'sync --exclude target src/ host:src/' in a build with remote support."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Sync, args)
+    }
+}
+
+impl Builtin for RcpCommand {
+    fn name(&self) -> &'static str {
+        "rcp"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Copy directory trees incrementally"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy files and directory trees, skipping what is already up to date by size and \
+         time or by checksum, optionally deleting extra files, filtered by include and \
+         exclude patterns."
+    }
+
+    fn usage(&self) -> &'static str {
+        "rcp [-acnuv] [--delete] [--size-only] [--exclude PATTERN] [--include PATTERN] \
+         [-e COMMAND] SRC... DEST"
+    }
+
+    fn help(&self) -> &'static str {
+        "Mirror directory trees, like 'sync'. Use 'rcp -av src/ backup/' to copy what \
+         changed."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Rcp, args)
+    }
+}
+
+fn execute_in(
+    ctx: &mut ShellContext,
+    flavor: Flavor,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let cwd = ctx.cwd.clone();
+    let outcome = run(flavor, args, &cwd, &|| ctx.is_timed_out());
+    Ok(ExecutionResult::success(outcome.status)
+        .with_output(outcome.stdout)
+        .with_error(outcome.stderr))
+}
+
+/// Run sync for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Sync, args)
+}
+
+/// Run rcp for the legacy dispatcher
+pub fn execute_rcp(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Rcp, args)
+}
+
+fn run_legacy(flavor: Flavor, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(flavor, args, &cwd, &|| false);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// Which name the command was run by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Sync,
+    Rcp,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Sync => "sync",
+            Flavor::Rcp => "rcp",
+        }
+    }
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+fn run(flavor: Flavor, args: &[String], cwd: &Path, stopped: &dyn Fn() -> bool) -> Outcome {
+    let name = flavor.name();
+    if args.is_empty() && flavor == Flavor::Sync {
+        flush();
+        return Outcome {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+        };
+    }
+    let usage = |message: String| Outcome {
+        stdout: Vec::new(),
+        stderr: format!("{name}: {message}\n").into_bytes(),
+        status: 1,
+    };
+    let mut options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => return usage(message),
+    };
+    let dest = match options.operands.pop() {
+        None => return usage("missing file operand".to_string()),
+        Some(dest) if options.operands.is_empty() => {
+            return usage(format!("missing destination file operand after '{dest}'"))
+        }
+        Some(dest) => dest,
+    };
+
+    let mut sync = Sync {
+        name,
+        options: &options,
+        stdout: String::new(),
+        stderr: String::new(),
+        status: 0,
+        files: 0,
+        bytes: 0,
+        deleted: 0,
+        stopped,
+    };
+    let mut to = match side(&options, cwd, &dest) {
+        Ok(to) => to,
+        Err(message) => return usage(message),
+    };
+    let mut sources = Vec::new();
+    for operand in &options.operands {
+        match side(&options, cwd, operand) {
+            Ok(from) if from.remote && to.remote => {
+                return usage("source and destination cannot both be remote".to_string())
+            }
+            Ok(from) => sources.push(from),
+            Err(message) => return usage(message),
+        }
+    }
+
+    // Several sources, or a destination that is or names a directory,
+    // put each source inside it
+    let into = sources.len() > 1
+        || dest.ends_with('/')
+        || matches!(to.side.stat(&to.path), Ok(Some(entry)) if entry.kind == Kind::Dir);
+    for from in &mut sources {
+        if (sync.stopped)() {
+            sync.status = STOPPED;
+            break;
+        }
+        sync.transfer(from, &mut to, into);
+    }
+    sync.finish()
+}
+
+/// Flush the file system buffers to disk
+fn flush() {
     #[cfg(unix)]
+    nix::unistd::sync();
+}
+
+/// Parsed command line
+#[derive(Debug, Default)]
+struct Options {
+    dry_run: bool,
+    verbose: bool,
+    checksum: bool,
+    size_only: bool,
+    update: bool,
+    delete: bool,
+    /// `--exclude` and `--include` in the order given
+    rules: Vec<Rule>,
+    /// `-e`, the command that reaches other hosts
+    rsh: Option<String>,
+    operands: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    let (name, inline) = match long.split_once('=') {
+                        Some((name, value)) => (name, Some(value)),
+                        None => (long, None),
+                    };
+                    if matches!(name, "--exclude" | "--include" | "--rsh") {
+                        let value = match inline {
+                            Some(value) => value.to_string(),
+                            None => iter
+                                .next()
+                                .cloned()
+                                .ok_or_else(|| format!("option '{name}' requires an argument"))?,
+                        };
+                        options.value(name, &value)?;
+                        continue;
+                    }
+                    if inline.is_some() {
+                        return Err(format!("option '{name}' doesn't allow an argument"));
+                    }
+                    match name {
+                        "--dry-run" => options.dry_run = true,
+                        "--verbose" => options.verbose = true,
+                        "--checksum" => options.checksum = true,
+                        "--size-only" => options.size_only = true,
+                        "--update" => options.update = true,
+                        "--delete" => options.delete = true,
+                        "--archive" | "--recursive" => {}
+                        _ => return Err(format!("unrecognized option '{long}'")),
+                    }
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'n' => options.dry_run = true,
+                            'v' => options.verbose = true,
+                            'c' => options.checksum = true,
+                            'u' => options.update = true,
+                            'a' | 'r' => {}
+                            'e' => {
+                                let attached = &short[2 + at..];
+                                let value = if attached.is_empty() {
+                                    iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?
+                                } else {
+                                    attached.to_string()
+                                };
+                                options.value("--rsh", &value)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => options.operands.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "--rsh" => self.rsh = Some(value.to_string()),
+            _ => self.rules.push(Rule::new(name == "--include", value)?),
+        }
+        Ok(())
+    }
+
+    /// Whether the rules let the path from the top of the transfer through
+    fn keeps(&self, path: &str, dir: bool) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(path, dir))
+            .is_none_or(|rule| rule.include)
+    }
+}
+
+/// An `--exclude` or `--include` PATTERN
+#[derive(Debug)]
+struct Rule {
+    include: bool,
+    pattern: glob::Pattern,
+    /// The PATTERN ended in `/` and matches only directories
+    dirs_only: bool,
+    /// The PATTERN started with `/` and matches from the top of the transfer
+    anchored: bool,
+    /// The PATTERN holds a `/` and matches paths rather than names
+    whole_path: bool,
+}
+
+impl Rule {
+    fn new(include: bool, text: &str) -> Result<Self, String> {
+        let dirs_only = text.len() > 1 && text.ends_with('/');
+        let body = if dirs_only {
+            &text[..text.len() - 1]
+        } else {
+            text
+        };
+        let anchored = body.starts_with('/');
+        let body = body.trim_start_matches('/');
+        let pattern =
+            glob::Pattern::new(body).map_err(|e| format!("invalid pattern '{text}': {e}"))?;
+        Ok(Rule {
+            include,
+            pattern,
+            dirs_only,
+            anchored,
+            whole_path: anchored || body.contains('/'),
+        })
+    }
+
+    fn matches(&self, path: &str, dir: bool) -> bool {
+        if self.dirs_only && !dir {
+            return false;
+        }
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        if self.anchored {
+            return self.pattern.matches_with(path, options);
+        }
+        if !self.whole_path {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            return self.pattern.matches_with(name, options);
+        }
+        // Without the anchor a path PATTERN may match from any component on
+        std::iter::once(path)
+            .chain(path.match_indices('/').map(|(at, _)| &path[at + 1..]))
+            .any(|tail| self.pattern.matches_with(tail, options))
+    }
+}
+
+/// `[USER@]HOST:PATH`, split into the host and the path on it
+pub(crate) fn remote_part(operand: &str) -> Option<(&str, &str)> {
+    let (host, path) = operand.split_once(':')?;
+    let drive = cfg!(windows) && host.len() == 1;
+    if host.is_empty() || host.contains(['/', '\\']) || drive {
+        return None;
+    }
+    Some((host, path))
+}
+
+/// An operand: the side it is on and its path there
+struct Place {
+    side: Box<dyn Side>,
+    path: String,
+    remote: bool,
+}
+
+fn side(options: &Options, cwd: &Path, operand: &str) -> Result<Place, String> {
+    let Some((host, path)) = remote_part(operand) else {
+        return Ok(Place {
+            side: Box::new(Local {
+                cwd: cwd.to_path_buf(),
+            }),
+            path: operand.to_string(),
+            remote: false,
+        });
+    };
+    #[cfg(feature = "remote")]
     {
-        // Use nix crate for safe system call instead of direct libc
-        use nix::unistd::sync;
-        sync();
+        let command: Vec<String> = options
+            .rsh
+            .as_deref()
+            .unwrap_or("ssh")
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if command.is_empty() {
+            return Err("the remote shell command is empty".to_string());
+        }
+        let path = if path.is_empty() { "." } else { path };
+        Ok(Place {
+            side: Box::new(Remote {
+                command,
+                host: host.to_string(),
+            }),
+            path: path.to_string(),
+            remote: true,
+        })
     }
-    #[cfg(windows)]
+    #[cfg(not(feature = "remote"))]
     {
-        use windows_sys::Win32::Storage::FileSystem::{FindFirstVolumeW, FindNextVolumeW, FindVolumeClose, CreateFileW, FlushFileBuffers, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL};
-        
-        
-        let mut vol_name: [u16; 1024] = [0; 1024];
-        let handle = unsafe { FindFirstVolumeW(vol_name.as_mut_ptr(), vol_name.len() as u32) };
-        if handle != std::ptr::null_mut() {
-            let mut cur_name = vol_name;
-            loop {
-                let h = unsafe { CreateFileW(cur_name.as_ptr(), 0, FILE_SHARE_READ|FILE_SHARE_WRITE, std::ptr::null_mut(), OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, std::ptr::null_mut()) };
-                if h != windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE {
-                    unsafe { FlushFileBuffers(h); windows_sys::Win32::Foundation::CloseHandle(h); }
+        let _ = (options, host, path);
+        Err(format!(
+            "'{operand}' is on another host, which needs a build with the 'remote' feature"
+        ))
+    }
+}
+
+/// `rel` under `root`, both written with `/`
+fn join(root: &str, rel: &str) -> String {
+    match (root, rel) {
+        (_, "") => root.to_string(),
+        ("", _) => rel.to_string(),
+        _ => format!("{}/{rel}", root.trim_end_matches('/')),
+    }
+}
+
+/// A run in progress
+struct Sync<'a> {
+    name: &'static str,
+    options: &'a Options,
+    stdout: String,
+    stderr: String,
+    status: i32,
+    files: usize,
+    bytes: u64,
+    deleted: usize,
+    stopped: &'a dyn Fn() -> bool,
+}
+
+impl Sync<'_> {
+    /// Whether changes are listed
+    fn reporting(&self) -> bool {
+        self.options.verbose || self.options.dry_run
+    }
+
+    fn report(&mut self, line: &str) {
+        if self.reporting() {
+            self.stdout.push_str(line);
+            self.stdout.push('\n');
+        }
+    }
+
+    fn error(&mut self, what: &str, e: &io::Error) {
+        let _ = writeln!(self.stderr, "{}: {what}: {}", self.name, io_message(e));
+        self.status = self.status.max(PARTIAL);
+    }
+
+    fn finish(mut self) -> Outcome {
+        if self.reporting() {
+            let files = if self.files == 1 { "file" } else { "files" };
+            let dry = if self.options.dry_run {
+                " (DRY RUN)"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                self.stdout,
+                "{} {files} transferred ({} bytes), {} deleted{dry}",
+                self.files, self.bytes, self.deleted
+            );
+        }
+        Outcome {
+            stdout: self.stdout.into_bytes(),
+            stderr: self.stderr.into_bytes(),
+            status: self.status,
+        }
+    }
+
+    /// Copy one SRC operand to DEST, into it when `into` is set
+    fn transfer(&mut self, from: &mut Place, to: &mut Place, into: bool) {
+        let top = match from.side.stat(&from.path) {
+            Ok(Some(top)) => top,
+            Ok(None) => {
+                let e = io::Error::from(io::ErrorKind::NotFound);
+                return self.error(&format!("cannot stat '{}'", from.side.show(&from.path)), &e);
+            }
+            Err(e) => {
+                return self.error(&format!("cannot stat '{}'", from.side.show(&from.path)), &e)
+            }
+        };
+        let contents = top.kind == Kind::Dir && from.path.ends_with('/');
+        let name = from
+            .path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        // Patterns see paths from the top of the transfer, which is the
+        // directory named by SRC unless only its contents are copied
+        let prefix = if contents {
+            String::new()
+        } else {
+            name.clone()
+        };
+        // A directory lands inside DEST, as a file does when DEST is one
+        let inside = into || top.kind == Kind::Dir;
+        let dest = if inside && !contents {
+            join(&to.path, &name)
+        } else {
+            to.path.clone()
+        };
+        if !contents && !self.options.keeps(&name, top.kind == Kind::Dir) {
+            return;
+        }
+        if top.kind != Kind::Dir {
+            if into && !self.options.dry_run {
+                if let Err(e) = to.side.mkdir(&to.path) {
+                    let what = format!("cannot create '{}'", to.side.show(&to.path));
+                    return self.error(&what, &e);
+                }
+            }
+            let old = match to.side.stat(&dest) {
+                Ok(old) => old,
+                Err(e) => return self.error(&format!("cannot stat '{}'", to.side.show(&dest)), &e),
+            };
+            let item = Item {
+                from: &from.path,
+                to: &dest,
+                shown: &name,
+                entry: &top,
+                old: old.as_ref(),
+            };
+            return self.update(&mut *from.side, &mut *to.side, item);
+        }
+
+        let options = self.options;
+        let keep = |path: &str, dir: bool| options.keeps(&join(&prefix, path), dir);
+        let sources = match from.side.scan(&from.path, &keep) {
+            Ok(sources) => sources,
+            Err(e) => {
+                return self.error(&format!("cannot read '{}'", from.side.show(&from.path)), &e)
+            }
+        };
+        let old = match to.side.stat(&dest) {
+            Ok(old) => old,
+            Err(e) => return self.error(&format!("cannot stat '{}'", to.side.show(&dest)), &e),
+        };
+        let mut present = BTreeMap::new();
+        match &old {
+            Some(entry) if entry.kind == Kind::Dir => match to.side.scan(&dest, &keep) {
+                Ok(entries) => present = entries,
+                Err(e) => return self.error(&format!("cannot read '{}'", to.side.show(&dest)), &e),
+            },
+            _ => {
+                let made = if self.options.dry_run {
+                    Ok(())
+                } else if old.is_some() {
+                    to.side.remove(&dest).and_then(|_| to.side.mkdir(&dest))
+                } else {
+                    to.side.mkdir(&dest)
+                };
+                if let Err(e) = made {
+                    return self.error(&format!("cannot create '{}'", to.side.show(&dest)), &e);
+                }
+                if old.is_none() {
+                    let line = format!("created directory {}", to.side.show(&dest));
+                    self.report(&line);
+                }
+            }
+        }
+
+        if self.options.delete {
+            // Deepest first, so that directories are empty when their turn comes
+            for (path, entry) in present.iter().rev() {
+                if sources.contains_key(path) {
+                    continue;
                 }
-                let res = unsafe { FindNextVolumeW(handle, cur_name.as_mut_ptr(), cur_name.len() as u32) };
-                if res == 0 { break; }
+                let shown = join(&prefix, path);
+                let target = join(&dest, path);
+                if !self.options.dry_run {
+                    if let Err(e) = to.side.remove(&target) {
+                        self.error(&format!("cannot delete '{}'", to.side.show(&target)), &e);
+                        continue;
+                    }
+                }
+                let slash = if entry.kind == Kind::Dir { "/" } else { "" };
+                self.report(&format!("deleting {shown}{slash}"));
+                self.deleted += 1;
+            }
+        }
+
+        let mut dirs = vec![(dest.clone(), top)];
+        for (path, entry) in &sources {
+            if (self.stopped)() {
+                self.status = STOPPED;
+                return;
+            }
+            let source = join(&from.path, path);
+            let target = join(&dest, path);
+            let shown = join(&prefix, path);
+            let item = Item {
+                from: &source,
+                to: &target,
+                shown: &shown,
+                entry,
+                old: present.get(path),
+            };
+            self.update(&mut *from.side, &mut *to.side, item);
+            if entry.kind == Kind::Dir {
+                dirs.push((target, entry.clone()));
+            }
+        }
+        if !self.options.dry_run {
+            if let Err(e) = to.side.settle(&dirs) {
+                self.error(
+                    &format!("cannot set times in '{}'", to.side.show(&dest)),
+                    &e,
+                );
             }
-            unsafe { FindVolumeClose(handle); }
         }
     }
-    Ok(())
+
+    /// Bring one entry up to date
+    fn update(&mut self, from: &mut dyn Side, to: &mut dyn Side, item: Item) {
+        let Item {
+            from: source,
+            to: target,
+            shown,
+            entry,
+            old,
+        } = item;
+        let dry_run = self.options.dry_run;
+        let result = match entry.kind {
+            Kind::Dir => {
+                if old.is_some_and(|old| old.kind == Kind::Dir) {
+                    return;
+                }
+                self.report(&format!("{shown}/"));
+                if dry_run {
+                    return;
+                }
+                clear(to, target, old).and_then(|_| to.mkdir(target))
+            }
+            Kind::Link => {
+                if old.is_some_and(|old| old.kind == Kind::Link && old.target == entry.target) {
+                    return;
+                }
+                self.report(&format!("{shown} -> {}", entry.target));
+                if dry_run {
+                    return;
+                }
+                clear(to, target, old).and_then(|_| to.link(target, &entry.target))
+            }
+            Kind::File => {
+                if let Some(old) = old.filter(|old| old.kind == Kind::File) {
+                    if self.options.update && old.mtime > entry.mtime {
+                        return;
+                    }
+                    match self.same(from, source, to, target, old, entry) {
+                        Ok(true) => return,
+                        Ok(false) => {}
+                        Err(e) => {
+                            return self.error(&format!("cannot compare '{shown}'"), &e);
+                        }
+                    }
+                }
+                self.report(shown);
+                self.files += 1;
+                self.bytes += entry.size;
+                if dry_run {
+                    return;
+                }
+                let cleared = match old {
+                    Some(old) if old.kind != Kind::File => clear(to, target, Some(old)),
+                    _ => Ok(()),
+                };
+                cleared
+                    .and_then(|_| from.open(source))
+                    .and_then(|mut data| to.put(target, &mut *data, entry))
+            }
+        };
+        if let Err(e) = result {
+            self.error(&format!("cannot copy '{shown}'"), &e);
+        }
+    }
+
+    /// Whether a file needs no copying by the comparison chosen
+    fn same(
+        &self,
+        from: &mut dyn Side,
+        source: &str,
+        to: &mut dyn Side,
+        target: &str,
+        old: &Entry,
+        entry: &Entry,
+    ) -> io::Result<bool> {
+        if old.size != entry.size {
+            return Ok(false);
+        }
+        if self.options.checksum {
+            return Ok(from.digest(source)? == to.digest(target)?);
+        }
+        Ok(self.options.size_only || old.mtime == entry.mtime)
+    }
+}
+
+/// Remove what stands where an entry of another kind goes
+fn clear(to: &mut dyn Side, target: &str, old: Option<&Entry>) -> io::Result<()> {
+    match old {
+        Some(_) => to.remove(target),
+        None => Ok(()),
+    }
+}
+
+/// One entry to bring up to date
+struct Item<'a> {
+    from: &'a str,
+    to: &'a str,
+    /// How the entry is listed
+    shown: &'a str,
+    entry: &'a Entry,
+    /// What DEST has there now
+    old: Option<&'a Entry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    File,
+    Dir,
+    Link,
+}
+
+/// What a side knows about a path
+#[derive(Debug, Clone)]
+struct Entry {
+    kind: Kind,
+    size: u64,
+    /// Modification time in whole seconds since the epoch
+    mtime: i64,
+    /// Permission bits, where the side has them
+    mode: Option<u32>,
+    /// Where a link points
+    target: String,
+}
+
+/// One end of a transfer, with paths written with `/`
+trait Side {
+    /// How a path is named in messages
+    fn show(&self, path: &str) -> String;
+
+    /// What is at `path`, without following a link there
+    fn stat(&mut self, path: &str) -> io::Result<Option<Entry>>;
+
+    /// Everything below the directory `root`, keyed by the path under it,
+    /// leaving out what `keep` turns down and all it holds
+    fn scan(
+        &mut self,
+        root: &str,
+        keep: &dyn Fn(&str, bool) -> bool,
+    ) -> io::Result<BTreeMap<String, Entry>>;
+
+    /// The SHA-256 of a file's content
+    fn digest(&mut self, path: &str) -> io::Result<String>;
+
+    fn open(&mut self, path: &str) -> io::Result<Box<dyn Read>>;
+
+    /// Write `data` to a file at `path` by way of a temporary one, giving it
+    /// `entry`'s permissions and time
+    fn put(&mut self, path: &str, data: &mut dyn Read, entry: &Entry) -> io::Result<()>;
+
+    /// Create the directory `path` and any missing parents
+    fn mkdir(&mut self, path: &str) -> io::Result<()>;
+
+    fn link(&mut self, path: &str, target: &str) -> io::Result<()>;
+
+    /// Remove `path`, and all it holds when it is a directory
+    fn remove(&mut self, path: &str) -> io::Result<()>;
+
+    /// Give directories their permissions and times once their contents are
+    /// in place, the deepest last in `dirs`
+    fn settle(&mut self, dirs: &[(String, Entry)]) -> io::Result<()>;
+}
+
+/// Paths on this machine, relative ones taken from the shell's directory
+struct Local {
+    cwd: PathBuf,
+}
+
+impl Local {
+    fn path(&self, path: &str) -> PathBuf {
+        self.cwd.join(path)
+    }
+}
+
+fn local_entry(metadata: &fs::Metadata, path: &Path) -> Entry {
+    let kind = if metadata.file_type().is_symlink() {
+        Kind::Link
+    } else if metadata.is_dir() {
+        Kind::Dir
+    } else {
+        Kind::File
+    };
+    let target = match kind {
+        Kind::Link => fs::read_link(path)
+            .map(|target| target.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    Entry {
+        kind,
+        size: if kind == Kind::File {
+            metadata.len()
+        } else {
+            0
+        },
+        mtime: filetime::FileTime::from_last_modification_time(metadata).unix_seconds(),
+        mode: mode(metadata),
+        target,
+    }
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &str, _path: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Give a local file or directory `entry`'s permissions and time
+fn set_meta(path: &Path, entry: &Entry) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = entry.mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    filetime::set_file_mtime(path, filetime::FileTime::from_unix_time(entry.mtime, 0))
+}
+
+impl Side for Local {
+    fn show(&self, path: &str) -> String {
+        path.to_string()
+    }
+
+    fn stat(&mut self, path: &str) -> io::Result<Option<Entry>> {
+        let path = self.path(path);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => Ok(Some(local_entry(&metadata, &path))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn scan(
+        &mut self,
+        root: &str,
+        keep: &dyn Fn(&str, bool) -> bool,
+    ) -> io::Result<BTreeMap<String, Entry>> {
+        let root = self.path(root);
+        let mut entries = BTreeMap::new();
+        let mut pending = vec![String::new()];
+        while let Some(dir) = pending.pop() {
+            for item in fs::read_dir(root.join(&dir))? {
+                let item = item?;
+                let path = join(&dir, &item.file_name().to_string_lossy());
+                let entry = local_entry(&fs::symlink_metadata(item.path())?, &item.path());
+                if !keep(&path, entry.kind == Kind::Dir) {
+                    continue;
+                }
+                if entry.kind == Kind::Dir {
+                    pending.push(path.clone());
+                }
+                entries.insert(path, entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn digest(&mut self, path: &str) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(self.path(path))?, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    fn open(&mut self, path: &str) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(File::open(self.path(path))?))
+    }
+
+    fn put(&mut self, path: &str, data: &mut dyn Read, entry: &Entry) -> io::Result<()> {
+        let target = self.path(path);
+        let name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let temp = target.with_file_name(format!(".{name}.sync{}", std::process::id()));
+        let written = File::create(&temp)
+            .and_then(|mut file| io::copy(data, &mut file).and_then(|_| file.flush()))
+            .and_then(|_| set_meta(&temp, entry))
+            .and_then(|_| fs::rename(&temp, &target));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written
+    }
+
+    fn mkdir(&mut self, path: &str) -> io::Result<()> {
+        fs::create_dir_all(self.path(path))
+    }
+
+    fn link(&mut self, path: &str, target: &str) -> io::Result<()> {
+        symlink(target, &self.path(path))
+    }
+
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        let path = self.path(path);
+        if fs::symlink_metadata(&path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    fn settle(&mut self, dirs: &[(String, Entry)]) -> io::Result<()> {
+        for (path, entry) in dirs.iter().rev() {
+            set_meta(&self.path(path), entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Paths on another host, reached by running shell commands there through
+/// `ssh` or the `-e` command
+#[cfg(feature = "remote")]
+struct Remote {
+    command: Vec<String>,
+    host: String,
+}
+
+/// What `find -printf` reports about each path: type, size, time and mode,
+/// then the path under the starting point and the link target
+#[cfg(feature = "remote")]
+const FIND_FORMAT: &str = "'%y %s %T@ %m\\0%P\\0%l\\0'";
+
+#[cfg(feature = "remote")]
+impl Remote {
+    fn spawn(&self, script: &str) -> io::Result<std::process::Child> {
+        use std::process::{Command, Stdio};
+        Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .arg(&self.host)
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+
+    /// Run `script` on the host, feeding it `input`, and return what it
+    /// printed
+    fn run(&self, script: &str, input: Option<&mut dyn Read>) -> io::Result<Vec<u8>> {
+        let mut child = self.spawn(script)?;
+        let mut stdin = child.stdin.take();
+        let fed = match (input, stdin.as_mut()) {
+            (Some(input), Some(stdin)) => io::copy(input, stdin).map(|_| ()),
+            _ => Ok(()),
+        };
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(failure(&self.command[0], output.status, &output.stderr));
+        }
+        fed.map(|_| output.stdout)
+    }
+
+    /// The entries `find` printed, keyed by their path under its start
+    fn parse(listing: &[u8]) -> BTreeMap<String, Entry> {
+        let fields: Vec<String> = listing
+            .split(|&byte| byte == 0)
+            .map(|field| String::from_utf8_lossy(field).into_owned())
+            .collect();
+        let mut entries = BTreeMap::new();
+        for record in fields.chunks_exact(3) {
+            let mut head = record[0].split(' ');
+            let kind = match head.next() {
+                Some("f") => Kind::File,
+                Some("d") => Kind::Dir,
+                Some("l") => Kind::Link,
+                _ => continue,
+            };
+            let size = head.next().and_then(|size| size.parse().ok()).unwrap_or(0);
+            let mtime = head
+                .next()
+                .and_then(|time| time.split('.').next())
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(0);
+            let mode = head
+                .next()
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok());
+            entries.insert(
+                record[1].clone(),
+                Entry {
+                    kind,
+                    size: if kind == Kind::File { size } else { 0 },
+                    mtime,
+                    mode,
+                    target: record[2].clone(),
+                },
+            );
+        }
+        entries
+    }
+}
+
+/// Single-quote `text` for a POSIX shell
+/// The error for a remote command that failed, from the last line it
+/// printed on standard error
+#[cfg(feature = "remote")]
+fn failure(command: &str, status: std::process::ExitStatus, stderr: &[u8]) -> io::Error {
+    let stderr = String::from_utf8_lossy(stderr);
+    match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => io::Error::other(line.trim().to_string()),
+        None => io::Error::other(format!("{command} failed ({status})")),
+    }
+}
+
+/// The output of a command on the host, whose failure shows once it is
+/// read to the end
+#[cfg(feature = "remote")]
+struct RemoteRead {
+    command: String,
+    child: std::process::Child,
+}
+
+#[cfg(feature = "remote")]
+impl Read for RemoteRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(stdout) = self.child.stdout.as_mut() else {
+            return Ok(0);
+        };
+        let read = stdout.read(buf)?;
+        if read > 0 || buf.is_empty() {
+            return Ok(read);
+        }
+        self.child.stdout = None;
+        let mut stderr = Vec::new();
+        if let Some(mut pipe) = self.child.stderr.take() {
+            pipe.read_to_end(&mut stderr)?;
+        }
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(failure(&self.command, status, &stderr));
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(feature = "remote")]
+impl Drop for RemoteRead {
+    fn drop(&mut self) {
+        if self.child.stdout.is_some() {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(feature = "remote")]
+impl Side for Remote {
+    fn show(&self, path: &str) -> String {
+        format!("{}:{path}", self.host)
+    }
+
+    fn stat(&mut self, path: &str) -> io::Result<Option<Entry>> {
+        let path = quote(path);
+        let script = format!(
+            "if [ -e {path} ] || [ -L {path} ]; then find {path} -maxdepth 0 -printf {FIND_FORMAT}; fi"
+        );
+        Ok(Remote::parse(&self.run(&script, None)?).remove(""))
+    }
+
+    fn scan(
+        &mut self,
+        root: &str,
+        keep: &dyn Fn(&str, bool) -> bool,
+    ) -> io::Result<BTreeMap<String, Entry>> {
+        let script = format!(
+            "cd {} && find . -mindepth 1 -printf {FIND_FORMAT}",
+            quote(root)
+        );
+        let mut entries = Remote::parse(&self.run(&script, None)?);
+        // Parents sort before what they hold, so a directory is turned down
+        // before anything in it comes up
+        let mut dropped: Vec<String> = Vec::new();
+        entries.retain(|path, entry| {
+            let inside = dropped.iter().any(|dir| {
+                path.strip_prefix(dir.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            });
+            if inside {
+                return false;
+            }
+            let dir = entry.kind == Kind::Dir;
+            if keep(path, dir) {
+                return true;
+            }
+            if dir {
+                dropped.push(path.clone());
+            }
+            false
+        });
+        Ok(entries)
+    }
+
+    fn digest(&mut self, path: &str) -> io::Result<String> {
+        let output = self.run(&format!("sha256sum -- {}", quote(path)), None)?;
+        let output = String::from_utf8_lossy(&output);
+        let digest = output.split_whitespace().next().unwrap_or_default();
+        Ok(digest.trim_start_matches('\\').to_string())
+    }
+
+    fn open(&mut self, path: &str) -> io::Result<Box<dyn Read>> {
+        let mut child = self.spawn(&format!("cat -- {}", quote(path)))?;
+        drop(child.stdin.take());
+        Ok(Box::new(RemoteRead {
+            command: self.command[0].clone(),
+            child,
+        }))
+    }
+
+    fn put(&mut self, path: &str, data: &mut dyn Read, entry: &Entry) -> io::Result<()> {
+        let quoted = quote(path);
+        let chmod = entry
+            .mode
+            .map(|mode| format!(" && chmod {mode:o} \"$t\""))
+            .unwrap_or_default();
+        let script = format!(
+            "t={quoted}.sync$$ && {{ cat > \"$t\"{chmod} && touch -d @{} \"$t\" && mv -f -- \"$t\" {quoted} || {{ rm -f -- \"$t\"; exit 1; }}; }}",
+            entry.mtime
+        );
+        self.run(&script, Some(data)).map(|_| ())
+    }
+
+    fn mkdir(&mut self, path: &str) -> io::Result<()> {
+        self.run(&format!("mkdir -p -- {}", quote(path)), None)
+            .map(|_| ())
+    }
+
+    fn link(&mut self, path: &str, target: &str) -> io::Result<()> {
+        let script = format!("ln -s -- {} {}", quote(target), quote(path));
+        self.run(&script, None).map(|_| ())
+    }
+
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        self.run(&format!("rm -rf -- {}", quote(path)), None)
+            .map(|_| ())
+    }
+
+    fn settle(&mut self, dirs: &[(String, Entry)]) -> io::Result<()> {
+        let mut script = String::from("true");
+        for (path, entry) in dirs.iter().rev() {
+            let path = quote(path);
+            if let Some(mode) = entry.mode {
+                let _ = write!(script, " && chmod {mode:o} {path}");
+            }
+            let _ = write!(script, " && touch -d @{} {path}", entry.mtime);
+        }
+        self.run(&script, None).map(|_| ())
+    }
 }
 
 #[cfg(test)]
-mod tests { use super::*; #[tokio::test] async fn sync_runs(){ sync_cli(&[]).await.unwrap(); }} 
+mod tests {
+    use super::*;
 
+    fn rule(text: &str) -> Rule {
+        Rule::new(false, text).unwrap()
+    }
+
+    #[test]
+    fn matches_patterns_like_rsync() {
+        assert!(rule("*.o").matches("src/main.o", false));
+        assert!(!rule("*.o").matches("src/main.rs", false));
+        assert!(rule("target/").matches("a/target", true));
+        assert!(!rule("target/").matches("a/target", false));
+        assert!(rule("/build").matches("build", true));
+        assert!(!rule("/build").matches("src/build", true));
+        assert!(rule("docs/*.md").matches("site/docs/a.md", false));
+        assert!(!rule("docs/*.md").matches("docs/a/b.md", false));
+    }
+
+    #[test]
+    fn the_first_matching_rule_decides() {
+        let args: Vec<String> = [
+            "--include",
+            "*.rs",
+            "--include",
+            "*/",
+            "--exclude=*",
+            "a",
+            "b",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let options = Options::parse(&args).unwrap();
+        assert!(options.keeps("src", true));
+        assert!(options.keeps("src/lib.rs", false));
+        assert!(!options.keeps("src/lib.o", false));
+        assert_eq!(options.operands, ["a", "b"]);
+    }
+
+    #[test]
+    fn tells_remote_operands() {
+        assert_eq!(remote_part("host:dir"), Some(("host", "dir")));
+        assert_eq!(remote_part("ann@host:"), Some(("ann@host", "")));
+        assert_eq!(remote_part("./a:b"), None);
+        assert_eq!(remote_part("dir"), None);
+        assert_eq!(join("a/", "b"), "a/b");
+        assert_eq!(join("a", ""), "a");
+        assert_eq!(join("", "b"), "b");
+    }
+}
@@ -0,0 +1,225 @@
+//! `tac` builtin - write files with their lines in reverse order
+//!
+//! Syntax:
+//!   tac [-b] [-r] [-s SEP] [FILE...]
+//!
+//! Each FILE, standard input when there are none, is split into records
+//! ended by SEP (a newline by default) and written last record first. `-b`
+//! attaches the separator to the start of the record that follows it
+//! instead of the end of the one before, and `-r` reads SEP as an extended
+//! regular expression.
+//!
+//! The exit status is 0 on success and 1 on any error.
+
+use crate::common::operands::Inputs;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crate::grep::translate;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use regex::bytes::Regex;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The `tac` builtin command implementation
+pub struct TacCommand;
+
+impl Builtin for TacCommand {
+    fn name(&self) -> &'static str {
+        "tac"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Write files with their lines in reverse order"
+    }
+
+    fn description(&self) -> &'static str {
+        "Write each FILE last line first, or split into records by SEP with -s."
+    }
+
+    fn usage(&self) -> &'static str {
+        "tac [-b] [-r] [-s SEP] [FILE...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Write files with their lines in reverse order. Use 'tac app.log' to read a log \
+         newest entry first."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run tac for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug)]
+struct Options {
+    before: bool,
+    separator: Regex,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut before = false;
+        let mut is_regex = false;
+        let mut separator = "\n".to_string();
+        let mut files = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    files.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--before" => before = true,
+                "--regex" => is_regex = true,
+                "--separator" => {
+                    separator = iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| "option '--separator' requires an argument".to_string())?;
+                }
+                long if long.starts_with("--separator=") => {
+                    separator = long["--separator=".len()..].to_string();
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'b' => before = true,
+                            'r' => is_regex = true,
+                            's' => {
+                                let attached = &short[2 + at..];
+                                separator = match attached {
+                                    "" => iter.next().cloned().ok_or_else(|| {
+                                        "option requires an argument -- 's'".to_string()
+                                    })?,
+                                    _ => attached.to_string(),
+                                };
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => files.push(arg.clone()),
+            }
+        }
+        if separator.is_empty() {
+            return Err("separator cannot be empty".to_string());
+        }
+        let pattern = if is_regex {
+            translate(&separator, true)?
+        } else {
+            regex::escape(&separator)
+        };
+        let separator = Regex::new(&pattern).map_err(|e| e.to_string())?;
+        if files.is_empty() {
+            files.push("-".to_string());
+        }
+        Ok(Options {
+            before,
+            separator,
+            files,
+        })
+    }
+}
+
+/// DATA with its records in reverse order
+fn reverse(data: &[u8], separator: &Regex, before: bool) -> Vec<u8> {
+    // Each record runs up to the end of its separator, or with `before`
+    // from the start of the separator ahead of it
+    let mut records = Vec::new();
+    let mut start = 0;
+    for found in separator
+        .find_iter(data)
+        .filter(|found| !found.as_bytes().is_empty())
+    {
+        let end = if before { found.start() } else { found.end() };
+        if end > start {
+            records.push(&data[start..end]);
+        }
+        start = end;
+    }
+    if start < data.len() {
+        records.push(&data[start..]);
+    }
+    let mut out = Vec::with_capacity(data.len());
+    for record in records.into_iter().rev() {
+        out.extend_from_slice(record);
+    }
+    out
+}
+
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut outcome = Outcome {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        status: 0,
+    };
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            outcome.stderr = format!("tac: {message}\n").into_bytes();
+            outcome.status = 1;
+            return outcome;
+        }
+    };
+    let mut inputs = Inputs::new(cwd, stdin);
+    for name in &options.files {
+        match inputs.read(name) {
+            Ok(data) => outcome
+                .stdout
+                .extend(reverse(&data, &options.separator, options.before)),
+            Err(e) => {
+                outcome
+                    .stderr
+                    .extend(format!("tac: {name}: {}\n", io_message(&e)).as_bytes());
+                outcome.status = 1;
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tac(args: &[&str], input: &str) -> String {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, Path::new("."), &mut input.as_bytes());
+        String::from_utf8(outcome.stdout).unwrap()
+    }
+
+    #[test]
+    fn reverses_records() {
+        assert_eq!(tac(&[], "a\nb\nc\n"), "c\nb\na\n");
+        assert_eq!(tac(&[], "a\nb"), "ba\n");
+        assert_eq!(tac(&["-s", ","], "1,2,3,"), "3,2,1,");
+        assert_eq!(tac(&["-b", "-s", "#"], "#a#b"), "#b#a");
+        assert_eq!(tac(&["-r", "-s", "[0-9]+"], "a1b22c"), "cb22a1");
+    }
+}
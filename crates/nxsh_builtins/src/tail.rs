@@ -1,3 +1,4 @@
+use crate::common::decompress::{self, Decompress};
 use crate::common::{BuiltinContext, BuiltinResult};
 use std::collections::VecDeque;
 use std::fs::File;
@@ -11,6 +12,7 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
     let mut follow = false;
     let mut quiet = false;
     let mut verbose = false;
+    let mut decompress = Decompress::Files;
     let mut files: Vec<String> = Vec::new();
 
     let mut i = 0;
@@ -47,6 +49,8 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
             "-f" | "--follow" => follow = true,
             "-q" | "--quiet" | "--silent" => quiet = true,
             "-v" | "--verbose" => verbose = true,
+            "-z" | "--decompress" => decompress = Decompress::Always,
+            "--no-decompress" => decompress = Decompress::Never,
             "-h" | "--help" => {
                 print_help();
                 return Ok(0);
@@ -103,9 +107,9 @@ pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32>
         }
 
         let result = if filename == "-" {
-            read_from_stdin(line_count, byte_count)
+            read_from_stdin(line_count, byte_count, decompress)
         } else {
-            read_from_file(filename, line_count, byte_count)
+            read_from_file(filename, line_count, byte_count, decompress)
         };
 
         if let Err(e) = result {
@@ -126,12 +130,21 @@ fn read_from_file(
     filename: &str,
     line_count: i64,
     byte_count: Option<u64>,
+    decompress: Decompress,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if !Path::new(filename).exists() {
         return Err("No such file or directory".to_string().into());
     }
 
     let mut file = File::open(filename)?;
+    if decompress.applies(false) && decompress::sniff(Path::new(filename))?.is_some() {
+        // Compressed data has to be expanded from the start
+        let reader = decompress::decompressed(file)?;
+        return match byte_count {
+            Some(bytes) => write_last_bytes(reader, bytes),
+            None => read_last_lines(reader, line_count),
+        };
+    }
 
     if let Some(bytes) = byte_count {
         read_last_bytes(&mut file, bytes)?;
@@ -146,28 +159,37 @@ fn read_from_file(
 fn read_from_stdin(
     line_count: i64,
     byte_count: Option<u64>,
+    decompress: Decompress,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let stdin = std::io::stdin();
+    let reader: Box<dyn BufRead> = match decompress.applies(true) {
+        true => decompress::decompressed(stdin.lock())?,
+        false => Box::new(stdin.lock()),
+    };
 
     if let Some(bytes) = byte_count {
-        // For stdin with byte count, we need to read all and keep last N bytes
-        let mut buffer = Vec::new();
-        stdin.lock().read_to_end(&mut buffer)?;
-
-        let start = if buffer.len() > bytes as usize {
-            buffer.len() - bytes as usize
-        } else {
-            0
-        };
-
-        std::io::Write::write_all(&mut std::io::stdout(), &buffer[start..])?;
+        write_last_bytes(reader, bytes)?;
     } else {
-        read_last_lines(stdin.lock(), line_count)?;
+        read_last_lines(reader, line_count)?;
     }
 
     Ok(())
 }
 
+/// Keep the last BYTE_COUNT bytes of a reader that cannot seek
+fn write_last_bytes<R: Read>(
+    mut reader: R,
+    byte_count: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    let start = buffer.len().saturating_sub(byte_count as usize);
+
+    std::io::Write::write_all(&mut std::io::stdout(), &buffer[start..])?;
+    Ok(())
+}
+
 fn read_last_lines<R: BufRead>(
     reader: R,
     line_count: i64,
@@ -225,6 +247,8 @@ fn print_help() {
     println!("  -n, --lines=NUM      output the last NUM lines, instead of the last 10");
     println!("  -q, --quiet, --silent never output headers giving file names");
     println!("  -v, --verbose        always output headers giving file names");
+    println!("  -z, --decompress     expand compressed standard input too");
+    println!("      --no-decompress  never expand gzip, bzip2, xz or zstd input");
     println!("  -h, --help           display this help and exit");
     println!();
     println!("NUM may have a multiplier suffix:");
@@ -0,0 +1,1494 @@
+//! `tar` builtin - create, extract and list archives
+//!
+//! Syntax:
+//!   tar {-c | -x | -t} [-f ARCHIVE] [OPTION...] [MEMBER...]
+//!   tar {c | x | t}[LETTERS] [VALUE...] [MEMBER...]
+//!
+//! In the second, old form the values of `f`, `C` and `X` follow the letter
+//! bundle in the order the letters appear. ARCHIVE defaults to `-`, standard
+//! input or output.
+//!
+//! Archives are written in the POSIX pax format: plain ustar headers, with an
+//! extended header ahead of any member whose name or link target does not fit
+//! in one. `--format=ustar` skips such members instead. Reading understands
+//! ustar, pax and GNU long names.
+//!
+//! `-z`, `-j`, `-J` and `--zstd` pick gzip, bzip2, xz or zstd compression;
+//! `-a` picks it from the archive's suffix when creating. Without one of them
+//! a compressed archive is recognised from its first bytes when read. bzip2
+//! archives can only be read, and zstd ones are written as stored frames.
+//!
+//! Extraction stays inside the target directory: a leading `/` is removed
+//! from member names, members holding a `..` component are skipped, existing
+//! files are unlinked instead of written through, and a symbolic link on the
+//! way to a member must resolve inside the directory. Modes are masked with
+//! the umask unless `-p` is given, modification times are restored unless
+//! `-m` is, and ownership is restored when running as root.
+//!
+//! Archives need the `compression-tar` feature, and each codec the feature
+//! of its compression command.
+
+#[cfg(feature = "compression-tar")]
+use crate::common::decompress;
+use crate::common::decompress::Codec;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::collections::VecDeque;
+#[cfg(feature = "compression-tar")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "compression-tar")]
+use std::fs;
+#[cfg(feature = "compression-tar")]
+use std::io::BufReader;
+use std::io::{self, Read, Write};
+use std::path::Path;
+#[cfg(feature = "compression-tar")]
+use std::path::PathBuf;
+
+/// The `tar` builtin command implementation
+pub struct TarCommand;
+
+impl Builtin for TarCommand {
+    fn name(&self) -> &'static str {
+        "tar"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Create, extract or list archives"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create (-c), extract (-x) or list (-t) ustar and pax archives, optionally \
+         compressed with gzip, bzip2, xz or zstd, keeping modes and modification times \
+         and never extracting outside the target directory."
+    }
+
+    fn usage(&self) -> &'static str {
+        "tar {-c|-x|-t} [-vpmkhOa] [-f ARCHIVE] [-C DIR] [-z|-j|-J|--zstd] \
+         [--exclude=PATTERN] [-X FILE] [--strip-components=N] [--format=pax|ustar] \
+         [MEMBER...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Create, extract or list archives. Use 'tar -czf out.tar.gz DIR' to pack a \
+         directory and 'tar -xf out.tar.gz' to unpack it."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run tar for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+pub fn tar_cli(args: &[String]) -> Result<(), anyhow::Error> {
+    let context = BuiltinContext::new();
+    match execute(args, &context)? {
+        0 => Ok(()),
+        _ => Err(anyhow::anyhow!("tar: archive operation failed")),
+    }
+}
+
+/// What a tar run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mode {
+    Create,
+    Extract,
+    List,
+}
+
+/// Parsed command line
+#[cfg_attr(not(feature = "compression-tar"), allow(dead_code))]
+struct Options {
+    mode: Mode,
+    archive: String,
+    directory: Option<String>,
+    verbose: usize,
+    codec: Option<Codec>,
+    auto_compress: bool,
+    /// `-p` or `--no-same-permissions`; by default only root keeps modes
+    preserve_permissions: Option<bool>,
+    touch: bool,
+    keep_old: bool,
+    dereference: bool,
+    same_owner: Option<bool>,
+    strip_components: usize,
+    to_stdout: bool,
+    excludes: Vec<glob::Pattern>,
+    ustar: bool,
+    members: Vec<String>,
+}
+
+/// Letters whose option takes a value
+fn takes_value(letter: char) -> bool {
+    matches!(letter, 'f' | 'C' | 'X')
+}
+
+fn set_mode(slot: &mut Option<Mode>, mode: Mode) -> Result<(), String> {
+    match slot {
+        Some(old) if *old != mode => {
+            Err("You may not specify more than one '-ctx' option".to_string())
+        }
+        _ => {
+            *slot = Some(mode);
+            Ok(())
+        }
+    }
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            mode: Mode::List,
+            archive: "-".to_string(),
+            directory: None,
+            verbose: 0,
+            codec: None,
+            auto_compress: false,
+            preserve_permissions: None,
+            touch: false,
+            keep_old: false,
+            dereference: false,
+            same_owner: None,
+            strip_components: 0,
+            to_stdout: false,
+            excludes: Vec::new(),
+            ustar: false,
+            members: Vec::new(),
+        };
+        let mut mode = None;
+        let mut queue: VecDeque<String> = args.iter().cloned().collect();
+        let missing = |letter: char| format!("option requires an argument -- '{letter}'");
+
+        if let Some(first) = queue.front().filter(|arg| !arg.starts_with('-')).cloned() {
+            queue.pop_front();
+            for letter in first.chars() {
+                let value = match takes_value(letter) {
+                    true => Some(queue.pop_front().ok_or_else(|| missing(letter))?),
+                    false => None,
+                };
+                options.short(letter, value, &mut mode)?;
+            }
+        }
+
+        let mut operands_only = false;
+        while let Some(arg) = queue.pop_front() {
+            if operands_only || arg == "-" || !arg.starts_with('-') {
+                options.members.push(arg);
+            } else if arg == "--" {
+                operands_only = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                options.long(name, inline, &mut queue, &mut mode)?;
+            } else {
+                let letters: Vec<char> = arg[1..].chars().collect();
+                for (index, &letter) in letters.iter().enumerate() {
+                    if takes_value(letter) {
+                        let rest: String = letters[index + 1..].iter().collect();
+                        let value = match rest.is_empty() {
+                            true => queue.pop_front().ok_or_else(|| missing(letter))?,
+                            false => rest,
+                        };
+                        options.short(letter, Some(value), &mut mode)?;
+                        break;
+                    }
+                    options.short(letter, None, &mut mode)?;
+                }
+            }
+        }
+
+        options.mode = mode.ok_or("You must specify one of the '-ctx' options")?;
+        if options.mode == Mode::Create && options.members.is_empty() {
+            return Err("Cowardly refusing to create an empty archive".to_string());
+        }
+        Ok(options)
+    }
+
+    fn set_codec(&mut self, codec: Codec) -> Result<(), String> {
+        match self.codec {
+            Some(old) if old != codec => Err("Conflicting compression options".to_string()),
+            _ => {
+                self.codec = Some(codec);
+                Ok(())
+            }
+        }
+    }
+
+    fn exclude(&mut self, pattern: &str) -> Result<(), String> {
+        let pattern = glob::Pattern::new(pattern)
+            .map_err(|e| format!("{pattern}: invalid exclude pattern: {e}"))?;
+        self.excludes.push(pattern);
+        Ok(())
+    }
+
+    fn exclude_from(&mut self, file: &str) -> Result<(), String> {
+        let content = std::fs::read_to_string(file)
+            .map_err(|e| format!("{file}: Cannot open: {}", io_message(&e)))?;
+        content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .try_for_each(|line| self.exclude(line))
+    }
+
+    fn short(
+        &mut self,
+        letter: char,
+        value: Option<String>,
+        mode: &mut Option<Mode>,
+    ) -> Result<(), String> {
+        let value = value.unwrap_or_default();
+        match letter {
+            'c' => set_mode(mode, Mode::Create)?,
+            'x' => set_mode(mode, Mode::Extract)?,
+            't' => set_mode(mode, Mode::List)?,
+            'f' => self.archive = value,
+            'C' => self.directory = Some(value),
+            'X' => self.exclude_from(&value)?,
+            'v' => self.verbose += 1,
+            'z' => self.set_codec(Codec::Gzip)?,
+            'j' => self.set_codec(Codec::Bzip2)?,
+            'J' => self.set_codec(Codec::Xz)?,
+            'a' => self.auto_compress = true,
+            'p' => self.preserve_permissions = Some(true),
+            'm' => self.touch = true,
+            'k' => self.keep_old = true,
+            'h' => self.dereference = true,
+            'O' => self.to_stdout = true,
+            _ => return Err(format!("invalid option -- '{letter}'")),
+        }
+        Ok(())
+    }
+
+    fn long(
+        &mut self,
+        name: &str,
+        inline: Option<String>,
+        queue: &mut VecDeque<String>,
+        mode: &mut Option<Mode>,
+    ) -> Result<(), String> {
+        let with_value = matches!(
+            name,
+            "file" | "directory" | "exclude" | "exclude-from" | "strip-components" | "format"
+        );
+        let value = match (with_value, inline) {
+            (true, Some(value)) => value,
+            (true, None) => queue
+                .pop_front()
+                .ok_or_else(|| format!("option '--{name}' requires an argument"))?,
+            (false, Some(_)) => return Err(format!("option '--{name}' doesn't allow an argument")),
+            (false, None) => String::new(),
+        };
+        match name {
+            "create" => set_mode(mode, Mode::Create)?,
+            "extract" | "get" => set_mode(mode, Mode::Extract)?,
+            "list" => set_mode(mode, Mode::List)?,
+            "file" => self.archive = value,
+            "directory" => self.directory = Some(value),
+            "exclude" => self.exclude(&value)?,
+            "exclude-from" => self.exclude_from(&value)?,
+            "strip-components" => {
+                self.strip_components = value
+                    .parse()
+                    .map_err(|_| format!("'{value}': Invalid number of elements"))?
+            }
+            "format" => {
+                self.ustar = match value.as_str() {
+                    "ustar" => true,
+                    "pax" | "posix" => false,
+                    _ => return Err(format!("'{value}': Invalid archive format")),
+                }
+            }
+            "verbose" => self.verbose += 1,
+            "gzip" | "gunzip" | "ungzip" => self.set_codec(Codec::Gzip)?,
+            "bzip2" => self.set_codec(Codec::Bzip2)?,
+            "xz" => self.set_codec(Codec::Xz)?,
+            "zstd" => self.set_codec(Codec::Zstd)?,
+            "auto-compress" => self.auto_compress = true,
+            "preserve-permissions" | "same-permissions" => self.preserve_permissions = Some(true),
+            "no-same-permissions" => self.preserve_permissions = Some(false),
+            "touch" => self.touch = true,
+            "keep-old-files" => self.keep_old = true,
+            "overwrite" => self.keep_old = false,
+            "dereference" => self.dereference = true,
+            "same-owner" => self.same_owner = Some(true),
+            "no-same-owner" => self.same_owner = Some(false),
+            "to-stdout" => self.to_stdout = true,
+            _ => return Err(format!("unrecognized option '--{name}'")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "compression-tar"))]
+fn run(args: &[String], _cwd: &Path, _stdin: &mut dyn Read) -> Outcome {
+    let message = match Options::parse(args) {
+        Ok(_) => "archive support is not built in; enable the compression-tar feature".to_string(),
+        Err(message) => message,
+    };
+    Outcome {
+        stdout: Vec::new(),
+        stderr: format!("tar: {message}\n").into_bytes(),
+        status: 2,
+    }
+}
+
+#[cfg(feature = "compression-tar")]
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome {
+                stdout: Vec::new(),
+                stderr: format!("tar: {message}\n").into_bytes(),
+                status: 2,
+            }
+        }
+    };
+    let mut tar = Tar::new(options, cwd);
+    let result = match tar.options.mode {
+        Mode::Create => tar.create(),
+        Mode::Extract | Mode::List => tar.read(stdin),
+    };
+    if let Err(message) = result {
+        tar.error(message);
+    }
+    if tar.status != 0 {
+        tar.stderr
+            .extend_from_slice(b"tar: Exiting with failure status due to previous errors\n");
+    }
+    Outcome {
+        stdout: tar.stdout,
+        stderr: tar.stderr,
+        status: tar.status,
+    }
+}
+
+/// Compresses an archive while it is written
+#[cfg(feature = "compression-tar")]
+enum Encoder<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compression-gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    /// A codec that compresses the whole archive once it is complete
+    Whole(Codec, Vec<u8>, W),
+}
+
+#[cfg(feature = "compression-tar")]
+impl<W: Write> Encoder<W> {
+    fn new(codec: Option<Codec>, sink: W) -> Result<Self, String> {
+        match codec {
+            None => Ok(Encoder::Plain(sink)),
+            #[cfg(feature = "compression-gzip")]
+            Some(Codec::Gzip) => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                sink,
+                flate2::Compression::default(),
+            ))),
+            Some(Codec::Bzip2) => Err("bzip2 archives can only be read, not created".to_string()),
+            #[cfg(feature = "compression-lzma")]
+            Some(Codec::Xz) => Ok(Encoder::Whole(Codec::Xz, Vec::new(), sink)),
+            #[cfg(feature = "compression-zstd")]
+            Some(Codec::Zstd) => Ok(Encoder::Whole(Codec::Zstd, Vec::new(), sink)),
+            #[allow(unreachable_patterns)]
+            Some(codec) => Err(format!("{} support is not built in", codec.name())),
+        }
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(sink) => Ok(sink),
+            #[cfg(feature = "compression-gzip")]
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Whole(codec, data, mut sink) => {
+                match codec {
+                    #[cfg(feature = "compression-lzma")]
+                    Codec::Xz => lzma_rs::xz_compress(&mut data.as_slice(), &mut sink)?,
+                    #[cfg(feature = "compression-zstd")]
+                    Codec::Zstd => crate::zstd::write_store_frame_stream(
+                        &mut sink,
+                        &mut data.as_slice(),
+                        data.len() as u64,
+                    )
+                    .map_err(|e| io::Error::other(e.to_string()))?,
+                    _ => {}
+                }
+                Ok(sink)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "compression-tar")]
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(sink) => sink.write(buf),
+            #[cfg(feature = "compression-gzip")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Whole(_, data, _) => {
+                data.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(sink) => sink.flush(),
+            #[cfg(feature = "compression-gzip")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Whole(..) => Ok(()),
+        }
+    }
+}
+
+/// The header fields of one archive member
+#[cfg(feature = "compression-tar")]
+#[derive(Clone)]
+struct Member {
+    name: String,
+    link: Option<String>,
+    kind: tar::EntryType,
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    user: Option<String>,
+    group: Option<String>,
+    mtime: u64,
+    size: u64,
+}
+
+#[cfg(feature = "compression-tar")]
+impl Member {
+    fn new(header: &tar::Header, name: String, link: Option<String>, size: u64) -> Self {
+        let text = |field: Result<Option<&str>, std::str::Utf8Error>| {
+            field
+                .ok()
+                .flatten()
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        Member {
+            name,
+            link,
+            kind: header.entry_type(),
+            mode: header.mode().unwrap_or(0o644),
+            uid: header.uid().unwrap_or(0),
+            gid: header.gid().unwrap_or(0),
+            user: text(header.username()),
+            group: text(header.groupname()),
+            mtime: header.mtime().unwrap_or(0),
+            size,
+        }
+    }
+}
+
+/// Render MODE like `ls -l`, after the file type letter KIND
+#[cfg(feature = "compression-tar")]
+fn mode_string(kind: char, mode: u32) -> String {
+    let bit = |mask: u32, letter: char| if mode & mask != 0 { letter } else { '-' };
+    let exec = |mask: u32, special: u32, set: char| match (mode & mask != 0, mode & special != 0) {
+        (true, true) => set,
+        (false, true) => set.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    [
+        kind,
+        bit(0o400, 'r'),
+        bit(0o200, 'w'),
+        exec(0o100, 0o4000, 's'),
+        bit(0o040, 'r'),
+        bit(0o020, 'w'),
+        exec(0o010, 0o2000, 's'),
+        bit(0o004, 'r'),
+        bit(0o002, 'w'),
+        exec(0o001, 0o1000, 't'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// Copy BYTES into a NUL-padded header FIELD
+#[cfg(feature = "compression-tar")]
+fn put(field: &mut [u8], bytes: &[u8]) {
+    field.fill(0);
+    let len = bytes.len().min(field.len());
+    field[..len].copy_from_slice(&bytes[..len]);
+}
+
+/// Split NAME into the ustar prefix and name fields, if it fits them
+#[cfg(feature = "compression-tar")]
+fn split_ustar(name: &[u8]) -> Option<(&[u8], &[u8])> {
+    if name.len() <= 100 {
+        return Some((&[], name));
+    }
+    (0..name.len().min(156))
+        .filter(|&at| name[at] == b'/')
+        .find(|&at| (1..=100).contains(&(name.len() - at - 1)))
+        .map(|at| (&name[..at], &name[at + 1..]))
+}
+
+/// One `LENGTH KEY=VALUE\n` pax extended header record
+#[cfg(feature = "compression-tar")]
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let body = key.len() + value.len() + 3;
+    let mut len = body;
+    while len != body + len.to_string().len() {
+        len = body + len.to_string().len();
+    }
+    let mut record = format!("{len} {key}=").into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+#[cfg(all(feature = "compression-tar", unix))]
+#[allow(clippy::unnecessary_cast)] // mode_t is narrower than u32 on some systems
+fn current_umask() -> u32 {
+    use nix::sys::stat::{umask, Mode};
+    let mask = umask(Mode::empty());
+    umask(mask);
+    mask.bits() as u32
+}
+
+/// State of one tar run
+#[cfg(feature = "compression-tar")]
+struct Tar {
+    options: Options,
+    cwd: PathBuf,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+    /// Name prefixes already reported as removed
+    removed: HashSet<String>,
+    /// First member name of each multiply linked file, by device and inode
+    links: HashMap<(u64, u64), String>,
+    /// Device and inode of the archive being written
+    archive_id: Option<(u64, u64)>,
+    /// Width of the owner and size columns in long listings
+    owner_width: usize,
+    umask: u32,
+    root: bool,
+}
+
+#[cfg(feature = "compression-tar")]
+impl Tar {
+    fn new(options: Options, cwd: &Path) -> Self {
+        #[cfg(unix)]
+        let (umask, root) = (current_umask(), nix::unistd::geteuid().is_root());
+        #[cfg(not(unix))]
+        let (umask, root) = (0, false);
+        Tar {
+            options,
+            cwd: cwd.to_path_buf(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+            removed: HashSet::new(),
+            links: HashMap::new(),
+            archive_id: None,
+            owner_width: 19,
+            umask,
+            root,
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.warn(message);
+        self.status = 2;
+    }
+
+    fn warn(&mut self, message: String) {
+        self.stderr
+            .extend_from_slice(format!("tar: {message}\n").as_bytes());
+    }
+
+    /// Print a verbose line, on stderr when stdout carries the archive or
+    /// extracted data
+    fn report(&mut self, line: String) {
+        let to_stderr = (self.options.mode == Mode::Create && self.options.archive == "-")
+            || (self.options.mode == Mode::Extract && self.options.to_stdout);
+        let out = if to_stderr {
+            &mut self.stderr
+        } else {
+            &mut self.stdout
+        };
+        out.extend_from_slice(line.as_bytes());
+        out.push(b'\n');
+    }
+
+    /// The directory members are taken from or extracted into
+    fn base(&self) -> PathBuf {
+        match &self.options.directory {
+            Some(directory) => self.cwd.join(directory),
+            None => self.cwd.clone(),
+        }
+    }
+
+    fn excluded(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('/');
+        let mut suffixes = std::iter::once(name).chain(
+            name.match_indices('/')
+                .map(|(at, _)| &name[at + 1..])
+                .filter(|suffix| !suffix.is_empty()),
+        );
+        suffixes.any(|suffix| self.options.excludes.iter().any(|p| p.matches(suffix)))
+    }
+
+    /// The line listing MEMBER: its name, or with `-v` a long listing
+    fn describe(&mut self, member: &Member) -> String {
+        if self.options.verbose == 0
+            || (self.options.mode != Mode::List && self.options.verbose < 2)
+        {
+            return member.name.clone();
+        }
+        let kind = member.kind;
+        let letter = if kind.is_dir() {
+            'd'
+        } else if kind.is_symlink() {
+            'l'
+        } else if kind.is_hard_link() {
+            'h'
+        } else if kind.is_fifo() {
+            'p'
+        } else if kind.is_character_special() {
+            'c'
+        } else if kind.is_block_special() {
+            'b'
+        } else {
+            '-'
+        };
+        let user = member
+            .user
+            .clone()
+            .unwrap_or_else(|| member.uid.to_string());
+        let group = member
+            .group
+            .clone()
+            .unwrap_or_else(|| member.gid.to_string());
+        let owner = format!("{user}/{group}");
+        let size = member.size.to_string();
+        self.owner_width = self.owner_width.max(owner.len() + size.len() + 1);
+        let pad = " ".repeat(self.owner_width - owner.len() - size.len());
+        let time = {
+            use chrono::TimeZone;
+            chrono::Local
+                .timestamp_opt(member.mtime as i64, 0)
+                .single()
+                .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| member.mtime.to_string())
+        };
+        let mut line = format!(
+            "{} {owner}{pad}{size} {time} {}",
+            mode_string(letter, member.mode),
+            member.name
+        );
+        match &member.link {
+            Some(link) if kind.is_symlink() => line.push_str(&format!(" -> {link}")),
+            Some(link) if kind.is_hard_link() => line.push_str(&format!(" link to {link}")),
+            _ => {}
+        }
+        line
+    }
+
+    fn create(&mut self) -> Result<(), String> {
+        let archive = self.options.archive.clone();
+        let codec = self.options.codec.or_else(|| {
+            self.options
+                .auto_compress
+                .then(|| Codec::from_suffix(&archive))
+                .flatten()
+        });
+        if archive == "-" {
+            let data = self.write_archive(Vec::new(), codec)?;
+            self.stdout.extend_from_slice(&data);
+            return Ok(());
+        }
+        let file = fs::File::create(self.cwd.join(&archive))
+            .map_err(|e| format!("{archive}: Cannot open: {}", io_message(&e)))?;
+        #[cfg(unix)]
+        if let Ok(metadata) = file.metadata() {
+            use std::os::unix::fs::MetadataExt;
+            self.archive_id = Some((metadata.dev(), metadata.ino()));
+        }
+        let mut sink = self.write_archive(io::BufWriter::new(file), codec)?;
+        sink.flush()
+            .map_err(|e| format!("{archive}: Cannot write: {}", io_message(&e)))
+    }
+
+    fn write_archive<W: Write>(&mut self, sink: W, codec: Option<Codec>) -> Result<W, String> {
+        let mut builder = tar::Builder::new(Encoder::new(codec, sink)?);
+        let base = self.base();
+        for member in self.options.members.clone() {
+            let name = self.archived_name(&member);
+            self.add(&mut builder, &base.join(&member), name)?;
+        }
+        let failed =
+            |e: io::Error| format!("{}: Cannot write: {}", self.options.archive, io_message(&e));
+        builder
+            .into_inner()
+            .and_then(Encoder::finish)
+            .map_err(failed)
+    }
+
+    /// MEMBER as named in the archive: relative, without anything up to its
+    /// last `..` component
+    fn archived_name(&mut self, member: &str) -> String {
+        let mut start = 0;
+        let mut offset = 0;
+        for part in member.split('/') {
+            offset += part.len() + 1;
+            if part == ".." {
+                start = offset.min(member.len());
+            }
+        }
+        let name = member[start..].trim_start_matches('/');
+        let removed = &member[..member.len() - name.len()];
+        if !removed.is_empty() && self.removed.insert(removed.to_string()) {
+            self.warn(format!("Removing leading `{removed}' from member names"));
+        }
+        match name.is_empty() {
+            true => ".".to_string(),
+            false => name.to_string(),
+        }
+    }
+
+    /// Archive PATH as NAME, and a directory's contents after it
+    fn add<W: Write>(
+        &mut self,
+        builder: &mut tar::Builder<W>,
+        path: &Path,
+        name: String,
+    ) -> Result<(), String> {
+        if self.excluded(&name) {
+            return Ok(());
+        }
+        let metadata = match self.options.dereference {
+            true => fs::metadata(path),
+            false => fs::symlink_metadata(path),
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.error(format!("{name}: Cannot stat: {}", io_message(&e)));
+                return Ok(());
+            }
+        };
+        #[cfg(unix)]
+        let id = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.dev(), metadata.ino())
+        };
+        #[cfg(unix)]
+        if Some(id) == self.archive_id {
+            self.warn(format!("{name}: file is the archive; not dumped"));
+            return Ok(());
+        }
+
+        let mut header = tar::Header::new_ustar();
+        header.set_metadata_in_mode(&metadata, tar::HeaderMode::Complete);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if let Some(user) = crate::find::account("/etc/passwd", metadata.uid()) {
+                let _ = header.set_username(&user);
+            }
+            if let Some(group) = crate::find::account("/etc/group", metadata.gid()) {
+                let _ = header.set_groupname(&group);
+            }
+        }
+        let file_type = metadata.file_type();
+
+        if file_type.is_dir() {
+            let name = format!("{}/", name.trim_end_matches('/'));
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_size(0);
+            self.append(builder, &mut header, &name, None, io::empty())?;
+            let mut children: Vec<_> = match fs::read_dir(path) {
+                Ok(entries) => entries
+                    .filter_map(Result::ok)
+                    .map(|e| e.file_name())
+                    .collect(),
+                Err(e) => {
+                    self.error(format!("{name}: Cannot open: {}", io_message(&e)));
+                    return Ok(());
+                }
+            };
+            children.sort();
+            for child in children {
+                let child_name = format!("{name}{}", child.to_string_lossy());
+                self.add(builder, &path.join(&child), child_name)?;
+            }
+        } else if file_type.is_symlink() {
+            let target = match fs::read_link(path) {
+                Ok(target) => target.to_string_lossy().into_owned(),
+                Err(e) => {
+                    self.error(format!("{name}: Cannot readlink: {}", io_message(&e)));
+                    return Ok(());
+                }
+            };
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            self.append(builder, &mut header, &name, Some(&target), io::empty())?;
+        } else if file_type.is_file() {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                if metadata.nlink() > 1 {
+                    if let Some(first) = self.links.get(&id).cloned() {
+                        header.set_entry_type(tar::EntryType::Link);
+                        header.set_size(0);
+                        return self.append(builder, &mut header, &name, Some(&first), io::empty());
+                    }
+                    self.links.insert(id, name.clone());
+                }
+            }
+            let mut file = match fs::File::open(path) {
+                Ok(file) => file,
+                Err(e) => {
+                    self.error(format!("{name}: Cannot open: {}", io_message(&e)));
+                    return Ok(());
+                }
+            };
+            // A file that shrinks while it is read is padded with zeros so
+            // that the archive stays well formed
+            let len = metadata.len();
+            header.set_size(len);
+            let data = (&mut file).take(len).chain(io::repeat(0)).take(len);
+            self.append(builder, &mut header, &name, None, data)?;
+        } else if is_fifo(&file_type) {
+            header.set_entry_type(tar::EntryType::Fifo);
+            header.set_size(0);
+            self.append(builder, &mut header, &name, None, io::empty())?;
+        } else {
+            self.warn(format!("{name}: file type not supported; not dumped"));
+        }
+        Ok(())
+    }
+
+    /// Write HEADER and DATA as member NAME, behind a pax extended header
+    /// when NAME or LINK does not fit in the ustar one
+    fn append<W: Write>(
+        &mut self,
+        builder: &mut tar::Builder<W>,
+        header: &mut tar::Header,
+        name: &str,
+        link: Option<&str>,
+        data: impl Read,
+    ) -> Result<(), String> {
+        let mut records = Vec::new();
+        let fields = split_ustar(name.as_bytes());
+        if fields.is_none() {
+            if self.options.ustar {
+                self.error(format!(
+                    "{name}: file name is too long (max 256); not dumped"
+                ));
+                return Ok(());
+            }
+            records.extend(pax_record("path", name.as_bytes()));
+        }
+        if let Some(link) = link.filter(|link| link.len() > 100) {
+            if self.options.ustar {
+                self.error(format!(
+                    "{name}: link name is too long (max 100); not dumped"
+                ));
+                return Ok(());
+            }
+            records.extend(pax_record("linkpath", link.as_bytes()));
+        }
+
+        let tail = |bytes: &[u8]| bytes[bytes.len().saturating_sub(100)..].to_vec();
+        if let Some(ustar) = header.as_ustar_mut() {
+            let (prefix, short) = fields.unwrap_or((&[], name.as_bytes()));
+            put(&mut ustar.prefix, prefix);
+            put(&mut ustar.name, &tail(short));
+            if let Some(link) = link {
+                put(&mut ustar.linkname, &tail(link.as_bytes()));
+            }
+        }
+
+        let failed =
+            |e: io::Error| format!("{}: Cannot write: {}", self.options.archive, io_message(&e));
+        if !records.is_empty() {
+            let base = name
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(name);
+            let mut extended = tar::Header::new_ustar();
+            extended.set_entry_type(tar::EntryType::XHeader);
+            extended.set_mode(0o644);
+            extended.set_mtime(header.mtime().unwrap_or(0));
+            extended.set_size(records.len() as u64);
+            if let Some(ustar) = extended.as_ustar_mut() {
+                put(&mut ustar.name, format!("PaxHeaders.0/{base}").as_bytes());
+            }
+            extended.set_cksum();
+            builder
+                .append(&extended, records.as_slice())
+                .map_err(failed)?;
+        }
+        header.set_cksum();
+        builder.append(header, data).map_err(failed)?;
+
+        if self.options.verbose > 0 {
+            let size = header.size().unwrap_or(0);
+            let member = Member::new(header, name.to_string(), link.map(str::to_string), size);
+            let line = self.describe(&member);
+            self.report(line);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, stdin: &mut dyn Read) -> Result<(), String> {
+        let archive = self.options.archive.clone();
+        let raw: Box<dyn Read + '_> = match archive.as_str() {
+            "-" => Box::new(stdin),
+            _ => Box::new(
+                fs::File::open(self.cwd.join(&archive))
+                    .map_err(|e| format!("{archive}: Cannot open: {}", io_message(&e)))?,
+            ),
+        };
+        let failed = |e: io::Error| format!("{archive}: Cannot read: {}", io_message(&e));
+        let input: Box<dyn Read + '_> = match self.options.codec {
+            Some(codec) => decompress::decoder(codec, BufReader::new(raw)).map_err(failed)?,
+            None => Box::new(decompress::decompressed(raw).map_err(failed)?),
+        };
+        let mut reader = tar::Archive::new(input);
+        let entries = reader.entries().map_err(failed)?;
+
+        let root =
+            match self.options.mode == Mode::Extract && !self.options.to_stdout {
+                true => {
+                    let base = self.base();
+                    Some(fs::canonicalize(&base).map_err(|e| {
+                        format!("{}: Cannot open: {}", base.display(), io_message(&e))
+                    })?)
+                }
+                false => None,
+            };
+        let mut matched = vec![false; self.options.members.len()];
+        let mut directories = Vec::new();
+
+        for entry in entries {
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.error(io_message(&e));
+                    break;
+                }
+            };
+            if entry.header().entry_type().is_pax_global_extensions() {
+                continue;
+            }
+            let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+            let link = entry
+                .link_name_bytes()
+                .map(|link| String::from_utf8_lossy(&link).into_owned());
+            let member = Member::new(entry.header(), name, link, entry.size());
+            if self.excluded(&member.name) {
+                continue;
+            }
+            if !self.options.members.is_empty() {
+                let selected = self.options.members.iter().position(|wanted| {
+                    let wanted = wanted.trim_end_matches('/');
+                    let name = member.name.trim_end_matches('/');
+                    name == wanted
+                        || name
+                            .strip_prefix(wanted)
+                            .is_some_and(|rest| rest.starts_with('/'))
+                });
+                match selected {
+                    Some(index) => matched[index] = true,
+                    None => continue,
+                }
+            }
+            match &root {
+                None if self.options.mode == Mode::List => {
+                    let line = self.describe(&member);
+                    self.stdout.extend_from_slice(line.as_bytes());
+                    self.stdout.push(b'\n');
+                }
+                None => {
+                    if self.options.verbose > 0 {
+                        let line = self.describe(&member);
+                        self.report(line);
+                    }
+                    if member.kind.is_file() || member.kind == tar::EntryType::Continuous {
+                        if let Err(e) = io::copy(&mut entry, &mut self.stdout) {
+                            self.error(format!("{}: Cannot read: {}", member.name, io_message(&e)));
+                        }
+                    }
+                }
+                Some(root) => {
+                    if let Err(message) = self.extract(&mut entry, &member, root, &mut directories)
+                    {
+                        self.error(format!("{}: {message}", member.name));
+                    }
+                }
+            }
+        }
+
+        // Directory modes and times go last, so that a read-only directory
+        // still takes its members and their creation does not bump its mtime
+        for (path, member) in directories.iter().rev() {
+            if let Err(message) = self.restore(path, member) {
+                self.error(format!("{}: {message}", member.name));
+            }
+        }
+        for (wanted, hit) in self.options.members.clone().iter().zip(matched) {
+            if !hit {
+                self.error(format!("{wanted}: Not found in archive"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Where member NAME goes below the target directory, or `None` when it
+    /// must be skipped
+    fn target_of(&mut self, name: &str) -> Result<Option<PathBuf>, String> {
+        let relative = name.trim_start_matches('/');
+        if relative.len() != name.len() && self.removed.insert("/".to_string()) {
+            self.warn("Removing leading `/' from member names".to_string());
+        }
+        let parts: Vec<&str> = relative
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect();
+        if parts.contains(&"..") {
+            return Err("Member name contains '..'".to_string());
+        }
+        Ok(parts
+            .get(self.options.strip_components..)
+            .filter(|parts| !parts.is_empty())
+            .map(|parts| parts.iter().collect()))
+    }
+
+    /// Create the directories along RELATIVE below ROOT, refusing symbolic
+    /// links that lead out of ROOT
+    fn make_dirs(&self, root: &Path, relative: &Path) -> Result<(), String> {
+        let mut current = root.to_path_buf();
+        for component in relative.components() {
+            current.push(component);
+            match fs::symlink_metadata(&current) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    let real = fs::canonicalize(&current).map_err(|e| io_message(&e))?;
+                    if !real.starts_with(root) || !real.is_dir() {
+                        return Err(format!(
+                            "Cannot extract through symbolic link {}",
+                            current.strip_prefix(root).unwrap_or(&current).display()
+                        ));
+                    }
+                }
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => return Err("Cannot mkdir: Not a directory".to_string()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir(&current)
+                    .map_err(|e| format!("Cannot mkdir: {}", io_message(&e)))?,
+                Err(e) => return Err(format!("Cannot stat: {}", io_message(&e))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Make way for a new member at TARGET
+    fn clear(&self, target: &Path) -> Result<(), String> {
+        let unlink = |e: io::Error| format!("Cannot unlink: {}", io_message(&e));
+        match fs::symlink_metadata(target) {
+            Err(_) => Ok(()),
+            Ok(_) if self.options.keep_old => Err("Cannot open: File exists".to_string()),
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(target).map_err(unlink),
+            Ok(_) => fs::remove_file(target).map_err(unlink),
+        }
+    }
+
+    fn extract<R: Read>(
+        &mut self,
+        entry: &mut tar::Entry<R>,
+        member: &Member,
+        root: &Path,
+        directories: &mut Vec<(PathBuf, Member)>,
+    ) -> Result<(), String> {
+        let Some(relative) = self.target_of(&member.name)? else {
+            return Ok(());
+        };
+        if self.options.verbose > 0 {
+            let line = self.describe(member);
+            self.report(line);
+        }
+        let target = root.join(&relative);
+        let kind = member.kind;
+        if kind.is_dir() {
+            self.make_dirs(root, &relative)?;
+            directories.push((target, member.clone()));
+            return Ok(());
+        }
+        if let Some(parent) = relative.parent() {
+            self.make_dirs(root, parent)?;
+        }
+        self.clear(&target)?;
+
+        if kind.is_hard_link() {
+            let link = member.link.as_deref().unwrap_or_default();
+            let source = match self.target_of(link)? {
+                Some(source) => root.join(source),
+                None => return Err(format!("Cannot hard link to '{link}'")),
+            };
+            let inside = source
+                .parent()
+                .and_then(|parent| fs::canonicalize(parent).ok())
+                .is_some_and(|parent| parent.starts_with(root));
+            if !inside {
+                return Err(format!(
+                    "Cannot hard link to '{link}': outside the target directory"
+                ));
+            }
+            return fs::hard_link(&source, &target)
+                .map_err(|e| format!("Cannot hard link to '{link}': {}", io_message(&e)));
+        }
+        if kind.is_symlink() {
+            let link = member.link.as_deref().unwrap_or_default();
+            make_symlink(link, &target)
+                .map_err(|e| format!("Cannot create symlink to '{link}': {}", io_message(&e)))?;
+        } else if kind.is_fifo() {
+            make_fifo(&target).map_err(|e| format!("Cannot mkfifo: {}", io_message(&e)))?;
+        } else if kind.is_character_special() || kind.is_block_special() {
+            return Err("Cannot mknod: Operation not supported".to_string());
+        } else {
+            if !(kind.is_file() || kind == tar::EntryType::Continuous || kind.is_gnu_sparse()) {
+                self.warn(format!(
+                    "{}: Unknown file type '{}', extracted as normal file",
+                    member.name,
+                    kind.as_byte() as char
+                ));
+            }
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&target)
+                .map_err(|e| format!("Cannot open: {}", io_message(&e)))?;
+            io::copy(entry, &mut file).map_err(|e| format!("Cannot write: {}", io_message(&e)))?;
+        }
+        self.restore(&target, member)
+    }
+
+    /// Give the extracted PATH the owner, mode and time MEMBER records
+    fn restore(&self, path: &Path, member: &Member) -> Result<(), String> {
+        let symlink = member.kind.is_symlink();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if self.options.same_owner.unwrap_or(self.root) {
+                let uid = member
+                    .user
+                    .as_deref()
+                    .and_then(|user| crate::find::account_id("/etc/passwd", user))
+                    .unwrap_or(member.uid as u32);
+                let gid = member
+                    .group
+                    .as_deref()
+                    .and_then(|group| crate::find::account_id("/etc/group", group))
+                    .unwrap_or(member.gid as u32);
+                std::os::unix::fs::lchown(path, Some(uid), Some(gid)).map_err(|e| {
+                    format!(
+                        "Cannot change ownership to uid {uid}, gid {gid}: {}",
+                        io_message(&e)
+                    )
+                })?;
+            }
+            if !symlink {
+                let mode = match self.options.preserve_permissions.unwrap_or(self.root) {
+                    true => member.mode & 0o7777,
+                    false => member.mode & 0o777 & !self.umask,
+                };
+                fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| {
+                    format!(
+                        "Cannot change mode to {}: {}",
+                        &mode_string('-', mode)[1..],
+                        io_message(&e)
+                    )
+                })?;
+            }
+        }
+        if !self.options.touch {
+            let mtime = filetime::FileTime::from_unix_time(member.mtime as i64, 0);
+            let result = match symlink {
+                true => filetime::set_symlink_file_times(path, mtime, mtime),
+                false => filetime::set_file_mtime(path, mtime),
+            };
+            result.map_err(|e| format!("Cannot utime: {}", io_message(&e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compression-tar")]
+fn is_fifo(file_type: &fs::FileType) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        file_type.is_fifo()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file_type;
+        false
+    }
+}
+
+#[cfg(feature = "compression-tar")]
+fn make_symlink(link: &str, target: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(link, target)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(link, target)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (link, target);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(feature = "compression-tar")]
+fn make_fifo(target: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use nix::sys::stat::Mode;
+        nix::unistd::mkfifo(target, Mode::from_bits_truncate(0o600)).map_err(io::Error::from)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = target;
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(all(test, feature = "compression-tar"))]
+mod tests {
+    use super::*;
+
+    fn tar(cwd: &Path, list: &[&str], stdin: &[u8]) -> (Vec<u8>, String, i32) {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        let outcome = run(&args, cwd, &mut &stdin[..]);
+        (
+            outcome.stdout,
+            String::from_utf8(outcome.stderr).unwrap(),
+            outcome.status,
+        )
+    }
+
+    fn listing(cwd: &Path, list: &[&str], stdin: &[u8]) -> String {
+        String::from_utf8(tar(cwd, list, stdin).0).unwrap()
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/deep")).unwrap();
+        fs::write(dir.path().join("src/a.txt"), "alpha\n").unwrap();
+        fs::write(dir.path().join("src/deep/b.o"), "object\n").unwrap();
+        dir
+    }
+
+    /// An archive holding one regular file called NAME
+    fn raw_archive(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        put(&mut header.as_ustar_mut().unwrap().name, name.as_bytes());
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn parses_old_and_new_style_options() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let options = Options::parse(&args(&["czvf", "out.tgz", "dir"])).unwrap();
+        assert_eq!(options.mode, Mode::Create);
+        assert_eq!(options.codec, Some(Codec::Gzip));
+        assert_eq!((options.archive.as_str(), options.verbose), ("out.tgz", 1));
+        assert_eq!(options.members, ["dir"]);
+
+        let options = Options::parse(&args(&[
+            "-xf",
+            "a.tar",
+            "-C",
+            "out",
+            "--strip-components=1",
+        ]))
+        .unwrap();
+        assert_eq!(options.directory.as_deref(), Some("out"));
+        assert_eq!(options.strip_components, 1);
+
+        for bad in [
+            &["-c"][..],
+            &["-f", "x"],
+            &["-cx", "a"],
+            &["-tzJ"],
+            &["-t", "--bogus"],
+        ] {
+            assert!(Options::parse(&args(bad)).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn frames_names_and_pax_records() {
+        assert_eq!(pax_record("path", b"abc"), b"12 path=abc\n");
+        let long = "d".repeat(90) + "/" + &"f".repeat(90);
+        assert_eq!(split_ustar(long.as_bytes()).unwrap().0.len(), 90);
+        assert!(split_ustar("x".repeat(120).as_bytes()).is_none());
+        assert_eq!(Codec::from_suffix("a.tar.zst"), Some(Codec::Zstd));
+        assert_eq!(Codec::detect(b"BZh91AY"), Some(Codec::Bzip2));
+        assert_eq!(mode_string('-', 0o4755), "-rwsr-xr-x");
+    }
+
+    #[test]
+    fn creates_lists_and_extracts() {
+        let dir = tree();
+        let cwd = dir.path();
+        let (_, err, status) = tar(cwd, &["-cf", "out.tar", "--exclude=*.o", "src"], b"");
+        assert_eq!((err.as_str(), status), ("", 0));
+        assert_eq!(
+            listing(cwd, &["-tf", "out.tar"], b""),
+            "src/\nsrc/a.txt\nsrc/deep/\n"
+        );
+
+        fs::create_dir(cwd.join("out")).unwrap();
+        let (_, err, status) = tar(
+            cwd,
+            &["-xf", "out.tar", "-C", "out", "--strip-components=1"],
+            b"",
+        );
+        assert_eq!((err.as_str(), status), ("", 0));
+        assert_eq!(
+            fs::read_to_string(cwd.join("out/a.txt")).unwrap(),
+            "alpha\n"
+        );
+        assert!(cwd.join("out/deep").is_dir());
+
+        let (_, err, status) = tar(cwd, &["-tf", "out.tar", "src/missing"], b"");
+        assert_eq!(status, 2);
+        assert!(err.contains("src/missing: Not found in archive"));
+    }
+
+    #[test]
+    fn writes_long_names_as_pax() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = "n".repeat(150);
+        fs::write(dir.path().join(&long), "x").unwrap();
+        let (archive, _, status) = tar(dir.path(), &["-cf", "-", &long], b"");
+        assert_eq!(status, 0);
+        assert_eq!(listing(dir.path(), &["-t"], &archive), format!("{long}\n"));
+
+        let (_, err, status) = tar(dir.path(), &["-cf", "-", "--format=ustar", &long], b"");
+        assert_eq!(status, 2);
+        assert!(err.contains("file name is too long"));
+    }
+
+    #[test]
+    fn refuses_to_extract_outside_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path();
+        let (_, err, status) = tar(cwd, &["-x"], &raw_archive("../evil", b"x"));
+        assert_eq!(status, 2);
+        assert!(err.contains("Member name contains '..'"));
+        assert!(!cwd.parent().unwrap().join("evil").exists());
+
+        let (_, err, status) = tar(cwd, &["-x"], &raw_archive("/abs.txt", b"y"));
+        assert_eq!(status, 0);
+        assert!(err.contains("Removing leading `/'"));
+        assert_eq!(fs::read(cwd.join("abs.txt")).unwrap(), b"y");
+
+        #[cfg(unix)]
+        {
+            let outside = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside.path(), cwd.join("escape")).unwrap();
+            let (_, err, status) = tar(cwd, &["-x"], &raw_archive("escape/file", b"z"));
+            assert_eq!(status, 2);
+            assert!(err.contains("Cannot extract through symbolic link escape"));
+            assert!(!outside.path().join("file").exists());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_modes_times_and_links() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tree();
+        let cwd = dir.path();
+        let script = cwd.join("src/run.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+        filetime::set_file_mtime(&script, filetime::FileTime::from_unix_time(1_000_000, 0))
+            .unwrap();
+        std::os::unix::fs::symlink("a.txt", cwd.join("src/link")).unwrap();
+        fs::hard_link(cwd.join("src/a.txt"), cwd.join("src/hard")).unwrap();
+
+        let (archive, _, status) = tar(cwd, &["-c", "src"], b"");
+        assert_eq!(status, 0);
+        let long = listing(cwd, &["-tv"], &archive);
+        assert!(long.contains("src/link -> a.txt"), "{long}");
+        assert!(long.contains("src/hard link to src/a.txt"), "{long}");
+
+        fs::create_dir(cwd.join("out")).unwrap();
+        let (_, err, status) = tar(cwd, &["-xp", "-C", "out"], &archive);
+        assert_eq!((err.as_str(), status), ("", 0));
+        let metadata = fs::metadata(cwd.join("out/src/run.sh")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&metadata).unix_seconds(),
+            1_000_000
+        );
+        assert_eq!(
+            fs::read_link(cwd.join("out/src/link")).unwrap(),
+            Path::new("a.txt")
+        );
+        assert_eq!(fs::read(cwd.join("out/src/hard")).unwrap(), b"alpha\n");
+
+        let (_, err, status) = tar(cwd, &["-xk", "-C", "out"], &archive);
+        assert_eq!(status, 2);
+        assert!(err.contains("src/a.txt: Cannot open: File exists"));
+    }
+
+    #[cfg(feature = "compression-zstd")]
+    #[test]
+    fn compresses_and_detects_codecs() {
+        let dir = tree();
+        let cwd = dir.path();
+        let (_, _, status) = tar(cwd, &["-caf", "out.tar.zst", "src/a.txt"], b"");
+        assert_eq!(status, 0);
+        let data = fs::read(cwd.join("out.tar.zst")).unwrap();
+        assert_eq!(Codec::detect(&data), Some(Codec::Zstd));
+        assert_eq!(listing(cwd, &["-tf", "out.tar.zst"], b""), "src/a.txt\n");
+
+        let (_, err, status) = tar(cwd, &["-cjf", "out.tbz", "src"], b"");
+        assert_eq!(status, 2);
+        assert!(err.contains("bzip2 archives can only be read"));
+    }
+}
@@ -1,297 +1,191 @@
-use std::io::{self, BufRead, Write, Read};
-use nxsh_core::{ShellError, ErrorKind}; use nxsh_core::error::RuntimeErrorKind;
-
+//! `tee` builtin - copy standard input to files and standard output
+//!
+//! Syntax:
+//!   tee [-aip] [FILE...]
+//!
+//! Each FILE is truncated, or appended to with `-a`, and receives standard
+//! input as it is read. A FILE that cannot be opened or written is reported
+//! and dropped while the others carry on. `-i` (ignore interrupts) and `-p`
+//! (diagnose write errors) are accepted for compatibility; a builtin is not
+//! interrupted mid-copy and always reports write errors.
+//!
+//! The exit status is 0 on success and 1 when a FILE failed.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The `tee` builtin command implementation
+pub struct TeeCommand;
+
+impl Builtin for TeeCommand {
+    fn name(&self) -> &'static str {
+        "tee"
+    }
 
-pub fn tee_cli(args: &[String]) -> Result<(), ShellError> {
-    if args.contains(&"--help".to_string()) {
-        print_help();
-        return Ok(());
+    fn synopsis(&self) -> &'static str {
+        "Copy standard input to files and standard output"
     }
 
-    let mut append = false;
-    let mut ignore_interrupts = false;
-    let mut files = Vec::new();
-    
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-a" | "--append" => append = true,
-            "-i" | "--ignore-interrupts" => ignore_interrupts = true,
-            arg if !arg.starts_with('-') => files.push(arg.to_string()),
-            _ => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: invalid option -- '{}'", args[i]))),
-        }
-        i += 1;
+    fn description(&self) -> &'static str {
+        "Copy standard input to standard output and to each FILE, truncating the \
+         files or with -a appending to them."
     }
-    
-    execute_tee(&files, append, ignore_interrupts)
-}
 
-fn print_help() {
-    println!("tee - read from standard input and write to standard output and files
+    fn usage(&self) -> &'static str {
+        "tee [-aip] [FILE...]"
+    }
 
-USAGE:
-    tee [OPTION]... [FILE]...
+    fn help(&self) -> &'static str {
+        "Copy standard input to files and standard output. Use 'tee -a LOG' to append."
+    }
 
-DESCRIPTION:
-    Copy standard input to each FILE, and also to standard output.
-    
-OPTIONS:
-    -a, --append              Append to the given FILEs, do not overwrite
-    -i, --ignore-interrupts   Ignore interrupt signals
-    -h, --help               Display this help and exit
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let outcome = run(args, &cwd, &mut ctx.stdin);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
 
-EXAMPLES:
-    # Write to file and stdout
-    echo \"hello\" | tee output.txt
-    
-    # Write to multiple files
-    ls -la | tee file1.txt file2.txt
-    
-    # Append to file instead of overwriting
-    echo \"new line\" | tee -a logfile.txt
-    
-    # Use in pipeline
-    cat input.txt | tee intermediate.txt | sort > sorted.txt
-    
-    # Ignore interrupts
-    long_running_command | tee -i output.log
+/// Run tee for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, &mut io::stdin().lock());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
 
-EXIT STATUS:
-    0   Success
-    1   Error writing to file or reading from stdin");
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
 }
 
-fn execute_tee(files: &[String], append: bool, _ignore_interrupts: bool) -> Result<(), ShellError> {
-    use std::fs::OpenOptions;
-    
-    // Open all output files
-    let mut file_handles = Vec::new();
-    for filename in files {
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(append)
-            .truncate(!append)
-            .open(filename)
-            .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
-        
-        file_handles.push((filename.clone(), file));
-    }
-    
-    // Read from stdin and write to stdout and all files
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut line_count = 0;
-    let mut byte_count = 0;
-    
-    for line_result in stdin.lock().lines() {
-        let line = match line_result {
-            Ok(line) => line,
-            Err(e) => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: error reading stdin: {e}"))),
-        };
-        
-        line_count += 1;
-        byte_count += line.len() + 1; // +1 for newline
-        
-        // Write to stdout
-        if let Err(e) = writeln!(stdout, "{line}") {
-            return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: error writing to stdout: {e}")));
-        }
-        
-        // Write to all files
-        for (filename, file) in &mut file_handles {
-            if let Err(e) = writeln!(file, "{line}") {
-                return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")));
+fn run(args: &[String], cwd: &Path, stdin: &mut dyn Read) -> Outcome {
+    let mut append = false;
+    let mut files = Vec::new();
+    let mut stderr = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flags = match arg.as_str() {
+            "--" => {
+                files.extend(iter.by_ref().cloned());
+                break;
             }
-        }
-        
-        // Flush stdout periodically
-        if line_count % 100 == 0 {
-            let _ = stdout.flush();
-            for (_, file) in &mut file_handles {
-                let _ = file.flush();
+            "--append" => "a",
+            "--ignore-interrupts" => "i",
+            "--output-error" => "p",
+            long if long.starts_with("--output-error=") => "p",
+            long if long.starts_with("--") => {
+                return usage(format!("unrecognized option '{long}'"));
+            }
+            short if short.len() > 1 && short.starts_with('-') => &short[1..],
+            _ => {
+                files.push(arg.clone());
+                continue;
+            }
+        };
+        for flag in flags.chars() {
+            match flag {
+                'a' => append = true,
+                'i' | 'p' => {}
+                _ => return usage(format!("invalid option -- '{flag}'")),
             }
         }
     }
-    
-    // Final flush
-    let _ = stdout.flush();
-    for (_, file) in &mut file_handles {
-        let _ = file.flush();
-    }
-    
-    if files.is_empty() {
-        println!("tee: processed {line_count} lines ({byte_count} bytes)");
-    }
-    
-    Ok(())
-}
 
-// Alternative implementation for handling binary data
-pub fn tee_binary(files: &[String], append: bool) -> Result<(), ShellError> {
-    use std::fs::OpenOptions;
-    use std::io::Read;
-    
-    // Open all output files
-    let mut file_handles = Vec::new();
-    for filename in files {
-        let file = OpenOptions::new()
+    let mut outputs: Vec<(String, File)> = Vec::new();
+    let mut status = 0;
+    for name in files {
+        let opened = OpenOptions::new()
             .create(true)
             .write(true)
             .append(append)
             .truncate(!append)
-            .open(filename)
-            .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
-        
-        file_handles.push((filename.clone(), file));
-    }
-    
-    // Read from stdin in chunks
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut buffer = [0u8; 8192];
-    let mut total_bytes = 0;
-    
-    loop {
-        match stdin.read(&mut buffer) {
-            Ok(0) => break, // EOF
-            Ok(n) => {
-                total_bytes += n;
-                let data = &buffer[..n];
-                
-                // Write to stdout
-                if let Err(e) = stdout.write_all(data) {
-                    return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: error writing to stdout: {e}")));
-                }
-                
-                // Write to all files
-                for (filename, file) in &mut file_handles {
-                    if let Err(e) = file.write_all(data) {
-                        return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")));
-                    }
-                }
-                
-                // Periodic flush
-                if total_bytes % 65536 == 0 {
-                    let _ = stdout.flush();
-                    for (_, file) in &mut file_handles {
-                        let _ = file.flush();
-                    }
-                }
-            },
-            Err(e) => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: error reading stdin: {e}"))),
-        }
-    }
-    
-    // Final flush
-    let _ = stdout.flush();
-    for (_, file) in &mut file_handles {
-        let _ = file.flush();
-    }
-    
-    Ok(())
-}
-
-// Advanced tee with buffering control
-pub struct TeeOptions {
-    pub append: bool,
-    pub ignore_interrupts: bool,
-    pub line_buffered: bool,
-    pub buffer_size: usize,
-}
-
-impl Default for TeeOptions {
-    fn default() -> Self {
-        Self {
-            append: false,
-            ignore_interrupts: false,
-            line_buffered: true,
-            buffer_size: 8192,
+            .open(cwd.join(&name));
+        match opened {
+            Ok(file) => outputs.push((name, file)),
+            Err(e) => {
+                stderr.extend(format!("tee: {name}: {}\n", io_message(&e)).as_bytes());
+                status = 1;
+            }
         }
     }
-}
 
-pub fn tee_advanced(files: &[String], options: &TeeOptions) -> Result<(), ShellError> {
-    use std::fs::OpenOptions;
-    use std::io::{BufReader, BufWriter};
-    
-    // Open all output files with buffering
-    let mut file_handles = Vec::new();
-    for filename in files {
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(options.append)
-            .truncate(!options.append)
-            .open(filename)
-            .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
-        
-        let buffered_file = if options.buffer_size > 0 {
-            BufWriter::with_capacity(options.buffer_size, file)
-        } else {
-            BufWriter::new(file)
-        };
-        
-        file_handles.push((filename.clone(), buffered_file));
-    }
-    
-    // Set up buffered I/O
-    let stdin = io::stdin();
-    let mut reader = BufReader::with_capacity(options.buffer_size, stdin.lock());
-    let mut stdout = BufWriter::new(io::stdout());
-    
-    if options.line_buffered {
-        // Line-by-line processing
-        let mut line = String::new();
-        while reader.read_line(&mut line)? > 0 {
-            // Write to stdout
-            stdout.write_all(line.as_bytes())?;
-            
-            // Write to all files
-            for (filename, file) in &mut file_handles {
-                file.write_all(line.as_bytes())
-                    .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
+    let mut stdout = Vec::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let len = match stdin.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                stderr.extend(format!("tee: standard input: {}\n", io_message(&e)).as_bytes());
+                status = 1;
+                break;
             }
-            
-            line.clear();
-        }
-    } else {
-        // Chunk-based processing
-        let mut buffer = vec![0u8; options.buffer_size];
-        loop {
-            match reader.read(&mut buffer)? {
-                0 => break, // EOF
-                n => {
-                    let data = &buffer[..n];
-                    
-                    // Write to stdout
-                    stdout.write_all(data)?;
-                    
-                    // Write to all files
-                    for (filename, file) in &mut file_handles {
-                        file.write_all(data)
-                            .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
-                    }
-                }
+        };
+        stdout.extend_from_slice(&buffer[..len]);
+        outputs.retain_mut(|(name, file)| match file.write_all(&buffer[..len]) {
+            Ok(()) => true,
+            Err(e) => {
+                stderr.extend(format!("tee: {name}: {}\n", io_message(&e)).as_bytes());
+                status = 1;
+                false
             }
-        }
+        });
     }
-    
-    // Flush all outputs
-    stdout.flush()?;
-    for (filename, file) in &mut file_handles {
-        file.flush()
-            .map_err(|e| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("tee: {filename}: {e}")))?;
+    Outcome {
+        stdout,
+        stderr,
+        status,
     }
-    
-    Ok(())
 }
 
+fn usage(message: String) -> Outcome {
+    Outcome {
+        stdout: Vec::new(),
+        stderr: format!("tee: {message}\n").into_bytes(),
+        status: 1,
+    }
+}
 
-
-
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_to_files_and_output() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("log"), "old\n").unwrap();
+        let args = vec!["-a".to_string(), "log".to_string(), "new".to_string()];
+        let outcome = run(&args, dir.path(), &mut &b"line\n"[..]);
+        assert_eq!(
+            (outcome.stdout.as_slice(), outcome.status),
+            (&b"line\n"[..], 0)
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("log")).unwrap(),
+            "old\nline\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("new")).unwrap(),
+            "line\n"
+        );
+
+        let args = vec!["missing/out".to_string()];
+        let outcome = run(&args, dir.path(), &mut &b"x"[..]);
+        assert_eq!((outcome.stdout.as_slice(), outcome.status), (&b"x"[..], 1));
+        assert!(String::from_utf8(outcome.stderr)
+            .unwrap()
+            .contains("No such file or directory"));
+    }
 }
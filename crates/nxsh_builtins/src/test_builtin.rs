@@ -1,368 +1,640 @@
-use std::fs;
-use std::path::PathBuf;
-use anyhow::Result;
-use nxsh_core::{ErrorKind, ShellError};
-use nxsh_core::error::RuntimeErrorKind;
+//! `test` and `[` builtins - evaluate conditional expressions
+//!
+//! Syntax:
+//!   test EXPRESSION
+//!   [ EXPRESSION ]
+//!
+//! The exit status is 0 when EXPRESSION is true and 1 when it is false or
+//! missing. `[` is the same command but needs `]` as its last argument.
+//!
+//! File operators, true when FILE exists and:
+//!   -e FILE          always
+//!   -f FILE          is a regular file
+//!   -d FILE          is a directory
+//!   -L FILE, -h FILE is a symbolic link
+//!   -r, -w, -x FILE  may be read, written or executed by the shell's user
+//!   -s FILE          is not empty
+//!   -b, -c FILE      is a block or character device
+//!   -p, -S FILE      is a named pipe or a socket
+//!   -k, -g, -u FILE  has its sticky, set-group-ID or set-user-ID bit set
+//!   -O, -G FILE      is owned by the shell's effective user or group
+//!   -N FILE          was modified since it was last read
+//!   -t FD            file descriptor FD is a terminal
+//!   F1 -nt F2        F1 is newer than F2, or F2 does not exist
+//!   F1 -ot F2        F1 is older than F2, or F1 does not exist
+//!   F1 -ef F2        F1 and F2 are the same file
+//!
+//! Relative names are taken from the shell's working directory, and all
+//! but `-L` follow symbolic links.
+//!
+//! String and integer operators:
+//!   STRING, -n STRING  STRING is not empty
+//!   -z STRING          STRING is empty
+//!   -v NAME            the shell variable NAME is set
+//!   S1 = S2, S1 == S2  the strings are equal; `!=` when they are not
+//!   S1 < S2, S1 > S2   S1 sorts before or after S2 byte by byte
+//!   N1 -eq N2          the integers are equal; likewise -ne, -lt, -le,
+//!                      -gt and -ge
+//!
+//! Expressions combine with `! EXPR`, `EXPR -a EXPR`, `EXPR -o EXPR` and
+//! `( EXPR )`, where `!` binds tightest and `-a` tighter than `-o`. With up
+//! to four arguments the POSIX rules by argument count apply first, so
+//! `test ! = x` and `[ -f ]` compare and test strings as written.
+//!
+//! Usage errors, such as a missing operand or a non-integer given to an
+//! integer operator, are reported with exit status 2.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The status for a malformed expression
+const USAGE: i32 = 2;
+
+/// The `test` builtin command implementation
+pub struct TestCommand;
+
+/// The `[` builtin command implementation
+pub struct BracketCommand;
+
+impl Builtin for TestCommand {
+    fn name(&self) -> &'static str {
+        "test"
+    }
 
-pub fn test_builtin_cli(args: Vec<String>) -> Result<()> {
-    if args.is_empty() {
-        // Empty test always returns false
-        std::process::exit(1);
+    fn synopsis(&self) -> &'static str {
+        "Evaluate a conditional expression"
     }
 
-    if args.contains(&"-h".to_string()) || args.contains(&"--help".to_string()) {
-        print_help();
-        return Ok(());
+    fn description(&self) -> &'static str {
+        "Exit with status 0 when EXPRESSION is true and 1 when it is false. \
+         Expressions test files, compare strings and integers, and combine \
+         with !, -a, -o and parentheses."
     }
 
-    let result = evaluate_test_expression(&args)?;
-    
-    // Exit with appropriate code
-    if result {
-        std::process::exit(0);
-    } else {
-        std::process::exit(1);
+    fn usage(&self) -> &'static str {
+        "test EXPRESSION"
     }
-}
 
-fn print_help() {
-    println!("Usage: test EXPRESSION");
-    println!("   or: [ EXPRESSION ]");
-    println!();
-    println!("Evaluate conditional expression and exit with status 0 (true) or 1 (false).");
-    println!();
-    println!("File operators:");
-    println!("  -e FILE        True if FILE exists");
-    println!("  -f FILE        True if FILE is a regular file");
-    println!("  -d FILE        True if FILE is a directory");
-    println!("  -r FILE        True if FILE is readable");
-    println!("  -w FILE        True if FILE is writable");
-    println!("  -x FILE        True if FILE is executable");
-    println!("  -s FILE        True if FILE exists and is not empty");
-    println!("  -L FILE        True if FILE is a symbolic link");
-    println!();
-    println!("String operators:");
-    println!("  -z STRING      True if STRING is empty");
-    println!("  -n STRING      True if STRING is not empty");
-    println!("  STRING         True if STRING is not empty");
-    println!("  STR1 = STR2    True if strings are equal");
-    println!("  STR1 != STR2   True if strings are not equal");
-    println!("  STR1 < STR2    True if STR1 sorts before STR2");
-    println!("  STR1 > STR2    True if STR1 sorts after STR2");
-    println!();
-    println!("Numeric operators:");
-    println!("  INT1 -eq INT2  True if integers are equal");
-    println!("  INT1 -ne INT2  True if integers are not equal");
-    println!("  INT1 -lt INT2  True if INT1 is less than INT2");
-    println!("  INT1 -le INT2  True if INT1 is less than or equal to INT2");
-    println!("  INT1 -gt INT2  True if INT1 is greater than INT2");
-    println!("  INT1 -ge INT2  True if INT1 is greater than or equal to INT2");
-    println!();
-    println!("Logical operators:");
-    println!("  ! EXPR         True if EXPR is false");
-    println!("  EXPR1 -a EXPR2 True if both expressions are true");
-    println!("  EXPR1 -o EXPR2 True if either expression is true");
-    println!("  ( EXPR )       Force precedence");
-    println!();
-    println!("Examples:");
-    println!("  test -f /etc/passwd      # Check if file exists");
-    println!("  test -z \"$VAR\"           # Check if variable is empty");
-    println!("  test 5 -gt 3             # Numeric comparison");
-    println!("  test \"$a\" = \"$b\"        # String comparison");
-    println!("  [ -d /home ] && echo \"Home exists\"");
+    fn help(&self) -> &'static str {
+        "Check a condition. Use 'test -f notes.txt' to see whether a file exists, \
+         'test \"$n\" -gt 10' to compare numbers or 'test -z \"$name\"' for an empty string."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Test, args)
+    }
 }
 
-fn evaluate_test_expression(args: &[String]) -> Result<bool> {
-    if args.is_empty() {
-        return Ok(false);
+impl Builtin for BracketCommand {
+    fn name(&self) -> &'static str {
+        "["
     }
 
-    // Handle single argument cases
-    if args.len() == 1 {
-        return Ok(evaluate_single_argument(&args[0]));
+    fn synopsis(&self) -> &'static str {
+        "Evaluate a conditional expression"
     }
 
-    // Handle unary operators
-    if args.len() == 2 {
-        return evaluate_unary_expression(&args[0], &args[1]);
+    fn description(&self) -> &'static str {
+        "The same as test, with a closing ] as the last argument."
     }
 
-    // Handle binary operators
-    if args.len() == 3 {
-        return evaluate_binary_expression(&args[0], &args[1], &args[2]);
+    fn usage(&self) -> &'static str {
+        "[ EXPRESSION ]"
     }
 
-    // Handle complex expressions with logical operators
-    evaluate_complex_expression(args)
+    fn help(&self) -> &'static str {
+        "Check a condition. Use 'if [ -d build ]; then ...' to branch on a directory \
+         or '[ \"$a\" = \"$b\" ] && echo same' to compare strings."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        execute_in(ctx, Flavor::Bracket, args)
+    }
 }
 
-fn evaluate_single_argument(arg: &str) -> bool {
-    // Non-empty string is true
-    !arg.is_empty()
+fn execute_in(
+    ctx: &mut ShellContext,
+    flavor: Flavor,
+    args: &[String],
+) -> ShellResult<ExecutionResult> {
+    let cwd = ctx.cwd.clone();
+    let outcome = run(flavor, args, &cwd, &|name| ctx.get_var(name).is_some());
+    Ok(ExecutionResult::success(outcome.status).with_error(outcome.stderr))
 }
 
-fn evaluate_unary_expression(operator: &str, operand: &str) -> Result<bool> {
-    match operator {
-        "-e" => Ok(path_exists(operand)),
-        "-f" => Ok(is_regular_file(operand)),
-        "-d" => Ok(is_directory(operand)),
-        "-r" => Ok(is_readable(operand)),
-        "-w" => Ok(is_writable(operand)),
-        "-x" => Ok(is_executable(operand)),
-        "-s" => Ok(is_non_empty_file(operand)),
-        "-L" => Ok(is_symlink(operand)),
-        "-z" => Ok(operand.is_empty()),
-        "-n" => Ok(!operand.is_empty()),
-        "!" => Ok(!evaluate_single_argument(operand)),
-        _ => Err(ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Unknown unary operator: {operator}")
-        ).into()),
-    }
+/// Run test for the legacy dispatcher, writing errors straight to the
+/// process's standard error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Test, args)
 }
 
-fn evaluate_binary_expression(left: &str, operator: &str, right: &str) -> Result<bool> {
-    match operator {
-        "=" | "==" => Ok(left == right),
-        "!=" => Ok(left != right),
-        "<" => Ok(left < right),
-        ">" => Ok(left > right),
-        "-eq" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num == right_num)
-        }
-        "-ne" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num != right_num)
-        }
-        "-lt" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num < right_num)
-        }
-        "-le" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num <= right_num)
-        }
-        "-gt" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num > right_num)
-        }
-        "-ge" => {
-            let left_num: i64 = left.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            let right_num: i64 = right.parse()
-                .map_err(|_| ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), "Invalid number"))?;
-            Ok(left_num >= right_num)
-        }
-        _ => Err(ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Unknown binary operator: {operator}")
-        ).into()),
-    }
+/// Run `[` for the legacy dispatcher
+pub fn execute_bracket(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Bracket, args)
 }
 
-fn evaluate_complex_expression(args: &[String]) -> Result<bool> {
-    // Handle parentheses
-    if args.len() >= 3 && args[0] == "(" && args[args.len() - 1] == ")" {
-        let inner_args = &args[1..args.len() - 1];
-        return evaluate_test_expression(inner_args);
-    }
-
-    // Handle negation
-    if !args.is_empty() && args[0] == "!" {
-        let rest = &args[1..];
-        let result = evaluate_test_expression(rest)?;
-        return Ok(!result);
-    }
-
-    // Find logical operators (-a, -o) with lowest precedence
-    // -o has lower precedence than -a
-    for (i, arg) in args.iter().enumerate() {
-        if arg == "-o" && i > 0 && i < args.len() - 1 {
-            let left = &args[..i];
-            let right = &args[i + 1..];
-            let left_result = evaluate_test_expression(left)?;
-            let right_result = evaluate_test_expression(right)?;
-            return Ok(left_result || right_result);
-        }
-    }
+fn run_legacy(flavor: Flavor, args: &[String]) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(flavor, args, &cwd, &|name| std::env::var_os(name).is_some());
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
 
-    for (i, arg) in args.iter().enumerate() {
-        if arg == "-a" && i > 0 && i < args.len() - 1 {
-            let left = &args[..i];
-            let right = &args[i + 1..];
-            let left_result = evaluate_test_expression(left)?;
-            let right_result = evaluate_test_expression(right)?;
-            return Ok(left_result && right_result);
+/// Which name the command was run by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Test,
+    Bracket,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Test => "test",
+            Flavor::Bracket => "[",
         }
     }
+}
 
-    // If no logical operators found, try as a simple expression
-    if args.len() >= 3 {
-        evaluate_binary_expression(&args[0], &args[1], &args[2])
-    } else if args.len() == 2 {
-        evaluate_unary_expression(&args[0], &args[1])
-    } else if args.len() == 1 {
-        Ok(evaluate_single_argument(&args[0]))
-    } else {
-        Ok(false)
+/// What a run printed and its exit status
+struct Outcome {
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+fn run(flavor: Flavor, args: &[String], cwd: &Path, is_set: &dyn Fn(&str) -> bool) -> Outcome {
+    let mut words: Vec<&str> = args.iter().map(String::as_str).collect();
+    if flavor == Flavor::Bracket && words.pop() != Some("]") {
+        return Outcome {
+            stderr: b"[: missing `]'\n".to_vec(),
+            status: USAGE,
+        };
+    }
+    let tester = Tester { cwd, is_set };
+    match tester.evaluate(&words) {
+        Ok(result) => Outcome {
+            stderr: Vec::new(),
+            status: if result { 0 } else { 1 },
+        },
+        Err(msg) => Outcome {
+            stderr: format!("{}: {msg}\n", flavor.name()).into_bytes(),
+            status: USAGE,
+        },
     }
 }
 
-// File test implementations
+/// Operators taking one operand
+const UNARY: &[&str] = &[
+    "-e", "-f", "-d", "-L", "-h", "-r", "-w", "-x", "-s", "-b", "-c", "-p", "-S", "-k", "-g", "-u",
+    "-O", "-G", "-N", "-t", "-z", "-n", "-v",
+];
 
-fn path_exists(path: &str) -> bool {
-    PathBuf::from(path).exists()
+/// Operators between two operands
+const BINARY: &[&str] = &[
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+fn is_unary(op: &str) -> bool {
+    UNARY.contains(&op)
 }
 
-fn is_regular_file(path: &str) -> bool {
-    if let Ok(metadata) = fs::metadata(path) {
-        metadata.is_file()
-    } else {
-        false
-    }
+fn is_binary(op: &str) -> bool {
+    BINARY.contains(&op)
 }
 
-fn is_directory(path: &str) -> bool {
-    if let Ok(metadata) = fs::metadata(path) {
-        metadata.is_dir()
-    } else {
-        false
-    }
+/// Evaluates expressions against the file system and the shell's variables
+struct Tester<'a> {
+    cwd: &'a Path,
+    is_set: &'a dyn Fn(&str) -> bool,
 }
 
-fn is_readable(path: &str) -> bool {
-    if !path_exists(path) {
-        return false;
+impl Tester<'_> {
+    /// Evaluate the arguments, by the POSIX rules for their count when there
+    /// are at most four
+    fn evaluate(&self, args: &[&str]) -> Result<bool, String> {
+        match *args {
+            [] => Ok(false),
+            [word] => Ok(!word.is_empty()),
+            ["!", word] => Ok(word.is_empty()),
+            [op, operand] if is_unary(op) => self.unary(op, operand),
+            [op, _] => Err(format!("{op}: unary operator expected")),
+            [left, op, right] if is_binary(op) => self.binary(left, op, right),
+            [left, "-a", right] => Ok(!left.is_empty() && !right.is_empty()),
+            [left, "-o", right] => Ok(!left.is_empty() || !right.is_empty()),
+            ["!", ..] if args.len() <= 4 => Ok(!self.evaluate(&args[1..])?),
+            ["(", word, ")"] => Ok(!word.is_empty()),
+            ["(", left, right, ")"] => self.evaluate(&[left, right]),
+            [_, op, _] => Err(format!("{op}: binary operator expected")),
+            _ => {
+                let mut parser = Parser {
+                    tester: self,
+                    args,
+                    pos: 0,
+                };
+                let result = parser.or()?;
+                match parser.args.get(parser.pos) {
+                    None => Ok(result),
+                    Some(_) => Err("too many arguments".to_string()),
+                }
+            }
+        }
+    }
+
+    fn unary(&self, op: &str, operand: &str) -> Result<bool, String> {
+        Ok(match op {
+            "-z" => operand.is_empty(),
+            "-n" => !operand.is_empty(),
+            "-v" => (self.is_set)(operand),
+            "-t" => {
+                use std::io::IsTerminal;
+                match operand.trim() {
+                    "0" => io::stdin().is_terminal(),
+                    "1" => io::stdout().is_terminal(),
+                    "2" => io::stderr().is_terminal(),
+                    fd if fd.parse::<i64>().is_ok() => false,
+                    _ => return Err(format!("{operand}: integer expression expected")),
+                }
+            }
+            _ => match self.path(operand) {
+                Some(path) => file_test(op, &path),
+                None => false,
+            },
+        })
+    }
+
+    fn binary(&self, left: &str, op: &str, right: &str) -> Result<bool, String> {
+        Ok(match op {
+            "=" | "==" => left == right,
+            "!=" => left != right,
+            "<" => left.as_bytes() < right.as_bytes(),
+            ">" => left.as_bytes() > right.as_bytes(),
+            "-nt" | "-ot" => {
+                let (newer, older) = if op == "-nt" {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                match (self.modified(newer), self.modified(older)) {
+                    (Some(a), Some(b)) => a > b,
+                    (Some(_), None) => true,
+                    _ => false,
+                }
+            }
+            "-ef" => match (self.path(left), self.path(right)) {
+                (Some(a), Some(b)) => same_file(&a, &b),
+                _ => false,
+            },
+            _ => {
+                let (a, b) = (integer(left)?, integer(right)?);
+                match op {
+                    "-eq" => a == b,
+                    "-ne" => a != b,
+                    "-lt" => a < b,
+                    "-le" => a <= b,
+                    "-gt" => a > b,
+                    _ => a >= b,
+                }
+            }
+        })
+    }
+
+    /// Where a file operand names, or None for the empty string, which
+    /// names no file
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        (!name.is_empty()).then(|| self.cwd.join(name))
     }
 
-    // Try to open file for reading
-    fs::File::open(path).is_ok()
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        let path = self.path(name)?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
 }
 
-fn is_writable(path: &str) -> bool {
-    let path_buf = PathBuf::from(path);
-    
-    if path_buf.exists() {
-        // Check if we can open existing file for writing
-        fs::OpenOptions::new().write(true).open(&path_buf).is_ok()
-    } else {
-        // Check if we can create file in parent directory
-        if let Some(parent) = path_buf.parent() {
-            parent.exists() && is_writable(parent.to_str().unwrap_or(""))
-        } else {
-            false
+/// A recursive descent over `-o`, `-a`, `!` and parentheses for expressions
+/// longer than the POSIX rules cover
+struct Parser<'a, 'b> {
+    tester: &'a Tester<'a>,
+    args: &'b [&'b str],
+    pos: usize,
+}
+
+impl<'b> Parser<'_, 'b> {
+    fn peek(&self, offset: usize) -> Option<&'b str> {
+        self.args.get(self.pos + offset).copied()
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+        while self.peek(0) == Some("-o") {
+            self.pos += 1;
+            let right = self.and()?;
+            result = result || right;
         }
+        Ok(result)
     }
-}
 
-fn is_executable(path: &str) -> bool {
-    if !path_exists(path) {
-        return false;
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.term()?;
+        while self.peek(0) == Some("-a") {
+            self.pos += 1;
+            let right = self.term()?;
+            result = result && right;
+        }
+        Ok(result)
     }
 
-    if cfg!(windows) {
-        // On Windows, check file extension
-        let path_lower = path.to_lowercase();
-        path_lower.ends_with(".exe") ||
-        path_lower.ends_with(".bat") ||
-        path_lower.ends_with(".cmd") ||
-        path_lower.ends_with(".ps1")
-    } else {
-        // On Unix-like systems, check execute permission
-        #[cfg(unix)] {
-            use std::os::unix::fs::PermissionsExt;
-            if let Ok(metadata) = fs::metadata(path) {
-                let permissions = metadata.permissions();
-                permissions.mode() & 0o111 != 0
-            } else {
-                false
+    fn term(&mut self) -> Result<bool, String> {
+        let Some(word) = self.peek(0) else {
+            return Err("argument expected".to_string());
+        };
+        if word == "!" {
+            self.pos += 1;
+            return Ok(!self.term()?);
+        }
+        if word == "(" {
+            self.pos += 1;
+            let result = self.or()?;
+            if self.peek(0) != Some(")") {
+                return Err("`)' expected".to_string());
             }
+            self.pos += 1;
+            return Ok(result);
         }
-        #[cfg(not(unix))] {
-            // On non-Unix systems, check file extension
-            let path_lower = path.to_lowercase();
-            path_lower.ends_with(".exe") || path_lower.ends_with(".com") ||
-            path_lower.ends_with(".bat") || path_lower.ends_with(".cmd")
+        if let (Some(op), Some(right)) = (self.peek(1), self.peek(2)) {
+            if is_binary(op) {
+                self.pos += 3;
+                return self.tester.binary(word, op, right);
+            }
+        }
+        if is_unary(word) {
+            let Some(operand) = self.peek(1) else {
+                return Err(format!("{word}: argument expected"));
+            };
+            self.pos += 2;
+            return self.tester.unary(word, operand);
         }
+        self.pos += 1;
+        Ok(!word.is_empty())
     }
 }
 
-fn is_non_empty_file(path: &str) -> bool {
-    if let Ok(metadata) = fs::metadata(path) {
-        metadata.is_file() && metadata.len() > 0
-    } else {
-        false
+/// Parse an integer operand, allowing surrounding blanks and a sign
+fn integer(text: &str) -> Result<i64, String> {
+    let trimmed = text.trim_matches([' ', '\t']);
+    let digits = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("{text}: integer expression expected"));
     }
+    trimmed
+        .strip_prefix('+')
+        .unwrap_or(trimmed)
+        .parse()
+        .map_err(|_| format!("{text}: integer expression expected"))
 }
 
-fn is_symlink(path: &str) -> bool {
-    if let Ok(metadata) = fs::symlink_metadata(path) {
-        metadata.file_type().is_symlink()
-    } else {
-        false
+fn file_test(op: &str, path: &Path) -> bool {
+    if matches!(op, "-L" | "-h") {
+        return std::fs::symlink_metadata(path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+    }
+    let Ok(meta) = std::fs::metadata(path) else {
+        return false;
+    };
+    match op {
+        "-e" => true,
+        "-f" => meta.is_file(),
+        "-d" => meta.is_dir(),
+        "-s" => meta.len() > 0,
+        "-N" => match (meta.modified(), meta.accessed()) {
+            (Ok(modified), Ok(accessed)) => modified > accessed,
+            _ => false,
+        },
+        "-r" => accessible(path, &meta, Access::Read),
+        "-w" => accessible(path, &meta, Access::Write),
+        "-x" => accessible(path, &meta, Access::Execute),
+        #[cfg(unix)]
+        _ => {
+            use std::os::unix::fs::{FileTypeExt, MetadataExt};
+            let kind = meta.file_type();
+            match op {
+                "-b" => kind.is_block_device(),
+                "-c" => kind.is_char_device(),
+                "-p" => kind.is_fifo(),
+                "-S" => kind.is_socket(),
+                "-k" => meta.mode() & 0o1000 != 0,
+                "-g" => meta.mode() & 0o2000 != 0,
+                "-u" => meta.mode() & 0o4000 != 0,
+                // SAFETY: geteuid and getegid have no preconditions
+                "-O" => meta.uid() == unsafe { libc::geteuid() },
+                "-G" => meta.gid() == unsafe { libc::getegid() },
+                _ => false,
+            }
+        }
+        #[cfg(not(unix))]
+        _ => false,
     }
 }
 
-// Utility functions for shell integration
-
-pub fn test_file_exists(path: &str) -> bool {
-    path_exists(path)
+enum Access {
+    Read,
+    Write,
+    Execute,
 }
 
-pub fn test_is_directory(path: &str) -> bool {
-    is_directory(path)
+/// Whether the shell's user may access PATH, like `access(2)`
+fn accessible(path: &Path, meta: &std::fs::Metadata, access: Access) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let _ = meta;
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mode = match access {
+            Access::Read => libc::R_OK,
+            Access::Write => libc::W_OK,
+            Access::Execute => libc::X_OK,
+        };
+        // SAFETY: `path` is a valid NUL-terminated string for the call
+        unsafe { libc::access(path.as_ptr(), mode) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        match access {
+            Access::Read => true,
+            Access::Write => !meta.permissions().readonly(),
+            Access::Execute => {
+                meta.is_dir()
+                    || path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+                        matches!(
+                            e.to_ascii_lowercase().as_str(),
+                            "exe" | "bat" | "cmd" | "com"
+                        )
+                    })
+            }
+        }
+    }
 }
 
-pub fn test_is_file(path: &str) -> bool {
-    is_regular_file(path)
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(x), Ok(y)) => x.dev() == y.dev() && x.ino() == y.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+            (Ok(x), Ok(y)) => x == y,
+            _ => false,
+        }
+    }
 }
 
-pub fn test_string_empty(s: &str) -> bool {
-    s.is_empty()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn test_strings_equal(a: &str, b: &str) -> bool {
-    a == b
-}
+    fn check(flavor: Flavor, cwd: &Path, line: &str) -> (i32, String) {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        let outcome = run(flavor, &args, cwd, &|name| name == "HOME");
+        (outcome.status, String::from_utf8(outcome.stderr).unwrap())
+    }
 
-pub fn test_numbers_equal(a: i64, b: i64) -> bool {
-    a == b
-}
+    fn status(line: &str) -> i32 {
+        check(Flavor::Test, Path::new("."), line).0
+    }
 
-pub fn test_number_greater(a: i64, b: i64) -> bool {
-    a > b
-}
+    #[test]
+    fn follows_the_posix_argument_count_rules() {
+        assert_eq!(status(""), 1);
+        assert_eq!(status("x"), 0);
+        assert_eq!(status("-f"), 0);
+        assert_eq!(status("! x"), 1);
+        assert_eq!(status("-n x"), 0);
+        assert_eq!(status("-z x"), 1);
+        assert_eq!(status("! = !"), 0);
+        assert_eq!(status("x -a y"), 0);
+        assert_eq!(status("( x )"), 0);
+        assert_eq!(status("! x = y"), 0);
+        assert_eq!(status("( -z x )"), 1);
+        assert_eq!(status("-v HOME"), 0);
+        assert_eq!(status("-v NOPE"), 1);
+    }
 
-pub fn test_number_less(a: i64, b: i64) -> bool {
-    a < b
-}
+    #[test]
+    fn compares_strings_and_integers() {
+        assert_eq!(status("abc = abc"), 0);
+        assert_eq!(status("abc != abc"), 1);
+        assert_eq!(status("abc < abd"), 0);
+        assert_eq!(status("b > a"), 0);
+        assert_eq!(status("10 -gt 9"), 0);
+        assert_eq!(status("-3 -lt +2"), 0);
+        assert_eq!(status("007 -eq 7"), 0);
+        assert_eq!(status("1 -ge 2"), 1);
+        assert_eq!(integer(" 42 "), Ok(42));
+        assert!(integer("0x10").is_err());
+        assert!(integer("-").is_err());
+    }
 
-// Shell condition evaluation
-pub fn evaluate_condition(condition: &str) -> Result<bool> {
-    let args: Vec<String> = condition.split_whitespace().map(|s| s.to_string()).collect();
-    evaluate_test_expression(&args)
-}
+    #[test]
+    fn combines_with_not_and_or_and_parentheses() {
+        assert_eq!(status("a = a -a b = c"), 1);
+        assert_eq!(status("a = a -o b = c"), 0);
+        assert_eq!(status("a = b -o a = a -a b = c"), 1);
+        assert_eq!(status("( a = b -o a = a ) -a ! b = c"), 0);
+        assert_eq!(status("! ( a = a ) -o -z x"), 1);
+        assert_eq!(status("-n x -a -z y -o 1 -eq 2"), 1);
+    }
 
+    #[test]
+    fn reports_malformed_expressions() {
+        let cwd = Path::new(".");
+        assert_eq!(
+            check(Flavor::Test, cwd, "x y"),
+            (2, "test: x: unary operator expected\n".to_string())
+        );
+        assert_eq!(
+            check(Flavor::Test, cwd, "a b c"),
+            (2, "test: b: binary operator expected\n".to_string())
+        );
+        assert_eq!(
+            check(Flavor::Test, cwd, "a -lt 1"),
+            (2, "test: a: integer expression expected\n".to_string())
+        );
+        assert_eq!(
+            check(Flavor::Test, cwd, "( a = a -a b"),
+            (2, "test: `)' expected\n".to_string())
+        );
+        assert_eq!(
+            check(Flavor::Test, cwd, "a = a b c d"),
+            (2, "test: too many arguments\n".to_string())
+        );
+        assert_eq!(
+            check(Flavor::Bracket, cwd, "-n x"),
+            (2, "[: missing `]'\n".to_string())
+        );
+        assert_eq!(check(Flavor::Bracket, cwd, "-n x ]"), (0, String::new()));
+        assert_eq!(check(Flavor::Bracket, cwd, "]"), (1, String::new()));
+    }
 
+    #[test]
+    fn tests_files_relative_to_the_working_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("full"), "data").unwrap();
+        std::fs::write(root.join("empty"), "").unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        let test = |line: &str| check(Flavor::Test, root, line).0;
+
+        assert_eq!(test("-e full"), 0);
+        assert_eq!(test("-f full"), 0);
+        assert_eq!(test("-f sub"), 1);
+        assert_eq!(test("-d sub"), 0);
+        assert_eq!(test("-s full"), 0);
+        assert_eq!(test("-s empty"), 1);
+        assert_eq!(test("-e missing"), 1);
+        assert_eq!(test("-r full"), 0);
+        assert_eq!(test("-L full"), 1);
+        assert_eq!(test("full -ef ./full"), 0);
+        assert_eq!(test("full -ef empty"), 1);
+        assert_eq!(test("full -nt missing"), 0);
+        assert_eq!(test("missing -ot full"), 0);
+        assert_eq!(test("-f full -a ! -d full"), 0);
+
+        // The empty string names no file, not the working directory
+        let args = ["-e".to_string(), String::new()];
+        assert_eq!(run(Flavor::Test, &args, root, &|_| false).status, 1);
+    }
 
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+    #[cfg(unix)]
+    #[test]
+    fn tests_links_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let script = root.join("run.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("run.sh", root.join("link")).unwrap();
+        std::os::unix::fs::symlink("gone", root.join("dangling")).unwrap();
+        let test = |line: &str| check(Flavor::Test, root, line).0;
+
+        assert_eq!(test("-x run.sh"), 0);
+        assert_eq!(test("-L link"), 0);
+        assert_eq!(test("-h link"), 0);
+        assert_eq!(test("-f link"), 0);
+        assert_eq!(test("-L dangling"), 0);
+        assert_eq!(test("-e dangling"), 1);
+        assert_eq!(test("link -ef run.sh"), 0);
+        assert_eq!(test("-O run.sh"), 0);
+    }
 }
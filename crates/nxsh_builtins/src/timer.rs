@@ -1,3 +1,4 @@
+use crate::common::parse_duration;
 use anyhow::{anyhow, Result};
 use std::process::Command;
 use std::time::{Duration, Instant};
@@ -22,7 +23,7 @@ pub fn timer_cli(args: &[String]) -> Result<()> {
         eprintln!("Examples:");
         eprintln!("  timer 5s");
         eprintln!("  timer 2m 'Break time!'");
-        eprintln!("  timer 1h30m 'Meeting time'");
+        eprintln!("  timer 1.5h 'Meeting time'");
         std::process::exit(1);
     }
 
@@ -33,7 +34,7 @@ pub fn timer_cli(args: &[String]) -> Result<()> {
         "Timer finished!".to_string()
     };
 
-    let timer_duration = parse_duration(duration_str)?;
+    let timer_duration = parse_duration(duration_str).map_err(|e| anyhow!("timer: {e}"))?;
     
     println!("Timer started for {} seconds", timer_duration.as_secs());
     println!("Message: {}", message);
@@ -95,61 +96,3 @@ pub fn timer_cli(args: &[String]) -> Result<()> {
     Ok(())
 }
 
-/// Parse duration string with support for compound durations (e.g., "1h30m5s")
-fn parse_duration(duration_str: &str) -> Result<Duration> {
-    let duration_str = duration_str.trim();
-    
-    if duration_str.is_empty() {
-        return Err(anyhow!("timer: invalid duration: empty string"));
-    }
-
-    let mut total_seconds = 0.0;
-    let mut current_number = String::new();
-    
-    for ch in duration_str.chars() {
-        if ch.is_ascii_digit() || ch == '.' {
-            current_number.push(ch);
-        } else if ch.is_alphabetic() {
-            if current_number.is_empty() {
-                return Err(anyhow!("timer: invalid duration format"));
-            }
-            
-            let number: f64 = current_number.parse()
-                .map_err(|_| anyhow!("timer: invalid number: {}", current_number))?;
-            
-            if number < 0.0 {
-                return Err(anyhow!("timer: invalid duration: negative time"));
-            }
-            
-            let unit = ch.to_lowercase().to_string();
-            let seconds = match unit.as_str() {
-                "s" => number,
-                "m" => number * 60.0,
-                "h" => number * 3600.0,
-                "d" => number * 86400.0,
-                _ => {
-                    return Err(anyhow!("timer: invalid duration unit: {}", unit));
-                }
-            };
-            
-            total_seconds += seconds;
-            current_number.clear();
-        } else {
-            return Err(anyhow!("timer: invalid character in duration: {}", ch));
-        }
-    }
-    
-    // Handle case where no unit is specified (assume seconds)
-    if !current_number.is_empty() {
-        let number: f64 = current_number.parse()
-            .map_err(|_| anyhow!("timer: invalid number: {}", current_number))?;
-        total_seconds += number;
-    }
-    
-    if total_seconds <= 0.0 {
-        return Err(anyhow!("timer: duration must be positive"));
-    }
-
-    Ok(Duration::from_secs_f64(total_seconds))
-}
-
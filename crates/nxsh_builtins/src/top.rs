@@ -1,1110 +1,731 @@
-//! `top` command - display and update sorted information about running processes
+//! `top` builtin - show the busiest processes, refreshed live
 //!
-//! Full top implementation with real-time monitoring, interactive controls, and system information
-
-use nxsh_core::error::RuntimeErrorKind;
-use nxsh_core::{Builtin, ErrorKind, ExecutionResult, ShellContext, ShellError, ShellResult};
-use std::io::{self};
-
-use crossterm::{
-    cursor,
-    event::{self, Event, KeyCode, KeyEvent},
-    execute,
-    terminal::{self, ClearType},
-};
-use std::thread;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-
-pub struct TopBuiltin;
-
-#[derive(Debug, Clone)]
-pub struct TopOptions {
-    pub delay: Duration,
-    pub iterations: Option<u32>,
-    pub batch_mode: bool,
-    pub sort_field: String,
-    pub reverse_sort: bool,
-    pub show_threads: bool,
-    pub show_idle: bool,
-    pub filter_user: Option<String>,
-    pub filter_pid: Option<u32>,
-    pub show_command_line: bool,
-    pub color_mode: bool,
-    pub secure_mode: bool,
-}
-
-impl Default for TopOptions {
-    fn default() -> Self {
-        Self {
-            delay: Duration::from_secs(3),
-            iterations: None,
-            batch_mode: false,
-            sort_field: "cpu".to_string(),
-            reverse_sort: true,
-            show_threads: false,
-            show_idle: true,
-            filter_user: None,
-            filter_pid: None,
-            show_command_line: false,
-            color_mode: true,
-            secure_mode: false,
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct SystemInfo {
-    pub uptime: Duration,
-    pub load_avg: (f64, f64, f64),
-    pub tasks_total: u32,
-    pub tasks_running: u32,
-    pub tasks_sleeping: u32,
-    pub tasks_stopped: u32,
-    pub tasks_zombie: u32,
-    pub cpu_user: f64,
-    pub cpu_system: f64,
-    pub cpu_nice: f64,
-    pub cpu_idle: f64,
-    pub cpu_wait: f64,
-    pub cpu_hi: f64,
-    pub cpu_si: f64,
-    pub cpu_steal: f64,
-    pub memory_total: u64,
-    pub memory_free: u64,
-    pub memory_used: u64,
-    pub memory_buffers: u64,
-    pub memory_cached: u64,
-    pub swap_total: u64,
-    pub swap_free: u64,
-    pub swap_used: u64,
-}
-
-#[derive(Debug, Clone)]
-pub struct TopProcess {
-    pub pid: u32,
-    pub ppid: u32,
-    pub user: String,
-    pub priority: i32,
-    pub nice: i32,
-    pub virtual_memory: u64,
-    pub resident_memory: u64,
-    pub shared_memory: u64,
-    pub state: String,
-    pub cpu_percent: f64,
-    pub memory_percent: f64,
-    pub cpu_time: Duration,
-    pub command: String,
-    pub threads: u32,
-}
-
-impl Builtin for TopBuiltin {
+//! Syntax:
+//!   top [-bci] [-n COUNT] [-d SECS] [-p PID,...] [-u USER] [-o FIELD]
+//!
+//! Draws the uptime and load average, the number of tasks in each state and
+//! the memory in use above a table of processes ordered by CPU use, and
+//! redraws it with fresh figures every few seconds. CPU use is measured over
+//! each interval, so 100% is one CPU kept busy.
+//!
+//! On a terminal the screen is taken over until `q` or Ctrl-C is pressed,
+//! and these keys change the view:
+//!   P, M, N, T   order by CPU use, memory, process ID or CPU time
+//!   R            reverse the order
+//!   c            show whole command lines instead of names
+//!   i            hide idle processes
+//!   k            send a signal to a process, asking for its ID and the
+//!                signal name or number; Enter takes the defaults and Esc
+//!                cancels
+//!
+//! Elsewhere, or with `-b`, the frames are printed one after another.
+//!
+//! Options:
+//!   -b          batch mode, printing frames instead of drawing the screen
+//!   -n COUNT    stop after COUNT frames
+//!   -d SECS     wait SECS between frames instead of 3
+//!   -p PID,...  show only these processes
+//!   -u USER     show only the processes of USER
+//!   -o FIELD    order by FIELD: %CPU, %MEM, PID or TIME
+//!   -c          show whole command lines
+//!   -i          hide idle processes
+//!
+//! The exit status is 0 on success and 1 if the processes could not be
+//! read.
+
+use crate::common::process_utils::execute_kill_target;
+use crate::common::wait::sleep_unless;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::{MemoryManager, ProcessEntry, ProcessSnapshot, ProcessStatus};
+use std::cmp::Reverse;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Seconds between frames without `-d`
+const DEFAULT_DELAY: f64 = 3.0;
+
+/// How long the first frame measures CPU use over
+const FIRST_DELAY: Duration = Duration::from_millis(200);
+
+/// The signal the kill prompt offers, SIGTERM
+const DEFAULT_SIGNAL: i32 = 15;
+
+/// The `top` builtin command implementation
+pub struct TopCommand;
+
+impl Builtin for TopCommand {
     fn name(&self) -> &'static str {
         "top"
     }
 
     fn synopsis(&self) -> &'static str {
-        "display and update sorted information about running processes"
-    }
-
-    fn help(&self) -> &'static str {
-        "Display and update sorted information about running processes in real-time"
+        "Show the busiest processes, refreshed live"
     }
 
     fn description(&self) -> &'static str {
-        "Display and update sorted information about running processes in real-time"
-    }
-
-    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
-        let options = parse_top_args(args)?;
-
-        if options.batch_mode {
-            run_batch_mode(&options)?;
-        } else {
-            run_interactive_mode(&options)?;
-        }
-
-        Ok(ExecutionResult::success(0))
+        "Show system load, memory and the processes using the most CPU, redrawn every \
+         few seconds, with keys to change the order and signal processes."
     }
 
     fn usage(&self) -> &'static str {
-        "top - display and update sorted information about running processes
-
-USAGE:
-    top [OPTIONS]
-
-OPTIONS:
-    -b              Batch mode operation
-    -c              Show command line instead of command name
-    -d DELAY        Delay between updates (seconds)
-    -i              Don't show idle tasks
-    -n ITERATIONS   Number of iterations before exit
-    -p PID          Monitor only specified process
-    -u USER         Monitor only specified user
-    -H              Show individual threads
-    -s              Secure mode (disable some commands)
-    --help          Display this help and exit
-
-INTERACTIVE COMMANDS:
-    h, ?            Help
-    k               Kill a process
-    r               Renice a process
-    q               Quit
-    c               Toggle command line display
-    f, F            Field management
-    o, O            Ordering fields
-    u               Filter by user
-    i               Toggle idle processes
-    t               Toggle CPU states
-    m               Toggle memory information
-    1               Toggle SMP view
-    H               Toggle threads
-    space           Update display
-    <, >            Move sort field
-    R               Reverse sort order
-
-SORT FIELDS:
-    P               %CPU
-    M               %MEM
-    N               PID
-    T               TIME+
-    A               Age (newest first)
-
-EXAMPLES:
-    top                     Start top in interactive mode
-    top -b -n 1             Show current processes once
-    top -d 5                Update every 5 seconds
-    top -u root             Show only root processes
-    top -p 1234             Monitor process 1234"
+        "top [-bci] [-n COUNT] [-d SECS] [-p PID,...] [-u USER] [-o FIELD]"
     }
-}
-
-fn parse_top_args(args: &[String]) -> ShellResult<TopOptions> {
-    let mut options = TopOptions {
-        delay: Duration::from_secs(3),
-        iterations: None,
-        batch_mode: false,
-        sort_field: "cpu".to_string(),
-        reverse_sort: true,
-        show_threads: false,
-        show_idle: true,
-        filter_user: None,
-        filter_pid: None,
-        show_command_line: false,
-        color_mode: true,
-        secure_mode: false,
-    };
 
-    let mut i = 0;
-    while i < args.len() {
-        let arg = &args[i];
+    fn help(&self) -> &'static str {
+        "Show the busiest processes, refreshed live. Press M to order by memory, k to \
+         signal a process and q to quit; use 'top -b -n 1' for a single listing."
+    }
 
-        match arg.as_str() {
-            "-b" => options.batch_mode = true,
-            "-c" => options.show_command_line = true,
-            "-i" => options.show_idle = false,
-            "-H" => options.show_threads = true,
-            "-s" => options.secure_mode = true,
-            "-d" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(ShellError::new(
-                        ErrorKind::RuntimeError(
-                            nxsh_core::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        "Option -d requires an argument",
-                    ));
-                }
-                let delay_secs: f64 = args[i].parse().map_err(|_| {
-                    ShellError::new(
-                        ErrorKind::RuntimeError(
-                            nxsh_core::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        "Invalid delay value",
-                    )
-                })?;
-                options.delay = Duration::from_secs_f64(delay_secs);
-            }
-            "-n" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                        "Option -n requires an argument",
-                    ));
-                }
-                options.iterations = Some(args[i].parse().map_err(|_| {
-                    ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                        "Invalid iteration count",
-                    )
-                })?);
-            }
-            "-p" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                        "Option -p requires an argument",
-                    ));
-                }
-                options.filter_pid = Some(args[i].parse().map_err(|_| {
-                    ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                        "Invalid PID",
-                    )
-                })?);
-            }
-            "-u" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(ShellError::new(
-                        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                        "Option -u requires an argument",
-                    ));
-                }
-                options.filter_user = Some(args[i].clone());
-            }
-            "--help" => {
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                    "Help requested",
-                ))
-            }
-            _ if arg.starts_with("-") => {
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                    format!("Unknown option: {arg}"),
-                ));
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("top: {message}\n").into_bytes()))
             }
-            _ => {
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                    format!("Unknown argument: {arg}"),
-                ))
-            }
-        }
-        i += 1;
+        };
+        let outcome = if !options.batch && ctx.is_interactive() && io::stdout().is_terminal() {
+            let result = Terminal::open(&mut *ctx.stdout)
+                .map_err(|e| io_message(&e))
+                .and_then(|mut screen| top(&options, &mut screen));
+            Outcome::new(result, Vec::new())
+        } else {
+            let mut screen = Plain {
+                out: Vec::new(),
+                stopped: || ctx.is_timed_out(),
+            };
+            let result = top(&options, &mut screen);
+            Outcome::new(result, screen.out)
+        };
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
-
-    Ok(options)
 }
 
-fn run_batch_mode(options: &TopOptions) -> ShellResult<()> {
-    let mut iteration = 0;
-
-    loop {
-        if let Some(max_iterations) = options.iterations {
-            if iteration >= max_iterations {
-                break;
-            }
+/// Run top for the legacy dispatcher, drawing on the process's terminal
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("top: {message}");
+            return Ok(1);
         }
-
-        let system_info = collect_system_info()?;
-        let processes = collect_top_processes(options)?;
-
-        display_batch_output(&system_info, &processes, options)?;
-
-        iteration += 1;
-
-        if options.iterations.is_some() || iteration == 0 {
-            thread::sleep(options.delay);
+    };
+    let result = if !options.batch && io::stdout().is_terminal() {
+        Terminal::open(io::stdout())
+            .map_err(|e| io_message(&e))
+            .and_then(|mut screen| top(&options, &mut screen))
+    } else {
+        let mut screen = Plain {
+            out: io::stdout(),
+            stopped: || false,
+        };
+        top(&options, &mut screen)
+    };
+    let outcome = Outcome::new(result, Vec::new());
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn new(result: Result<(), String>, stdout: Vec<u8>) -> Self {
+        match result {
+            Ok(()) => Outcome {
+                stdout,
+                stderr: Vec::new(),
+                status: 0,
+            },
+            Err(message) => Outcome {
+                stdout,
+                stderr: format!("top: {message}\n").into_bytes(),
+                status: 1,
+            },
         }
     }
-
-    Ok(())
 }
 
-fn run_interactive_mode(options: &TopOptions) -> ShellResult<()> {
-    // Enable raw mode for interactive input
-    terminal::enable_raw_mode().map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to enable raw mode: {e}"),
-        )
-    })?;
-
-    let result = run_interactive_loop(options);
-
-    // Restore terminal
-    terminal::disable_raw_mode().map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to disable raw mode: {e}"),
-        )
-    })?;
-    execute!(
-        io::stdout(),
-        terminal::Clear(ClearType::All),
-        cursor::MoveTo(0, 0)
-    )
-    .map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to clear terminal: {e}"),
-        )
-    })?;
-
-    result
+/// What the table is ordered by
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Cpu,
+    Mem,
+    Pid,
+    Time,
 }
 
-fn run_interactive_loop(options: &TopOptions) -> ShellResult<()> {
-    let mut current_options = options.clone();
-    let mut last_update = Instant::now();
-
-    loop {
-        // Check for input
-        if event::poll(Duration::from_millis(100)).map_err(|e| {
-            ShellError::new(
-                ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                format!("Failed to poll events: {e}"),
-            )
-        })? {
-            if let Event::Key(key_event) = event::read().map_err(|e| {
-                ShellError::new(
-                    ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                    format!("Failed to read event: {e}"),
-                )
-            })? {
-                match handle_key_event(key_event, &mut current_options)? {
-                    KeyAction::Quit => break,
-                    KeyAction::Update => {
-                        last_update = Instant::now();
-                        update_display(&current_options)?;
+#[derive(Debug)]
+struct Options {
+    batch: bool,
+    iterations: Option<usize>,
+    delay: Duration,
+    pids: Vec<u32>,
+    user: Option<String>,
+    sort: SortKey,
+    full_command: bool,
+    hide_idle: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            batch: false,
+            iterations: None,
+            delay: Duration::from_secs_f64(DEFAULT_DELAY),
+            pids: Vec::new(),
+            user: None,
+            sort: SortKey::Cpu,
+            full_command: false,
+            hide_idle: false,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                return Err(format!("unexpected argument '{arg}'"));
+            };
+            for (at, flag) in flags.char_indices() {
+                match flag {
+                    'b' => options.batch = true,
+                    'c' => options.full_command = true,
+                    'i' => options.hide_idle = true,
+                    'n' | 'd' | 'p' | 'u' | 'o' => {
+                        let value = match &flags[1 + at..] {
+                            "" => iter.next().cloned().ok_or_else(|| {
+                                format!("option requires an argument -- '{flag}'")
+                            })?,
+                            attached => attached.to_string(),
+                        };
+                        options.apply(flag, &value)?;
+                        break;
                     }
-                    KeyAction::Continue => {}
+                    _ => return Err(format!("invalid option -- '{flag}'")),
                 }
             }
         }
+        Ok(options)
+    }
 
-        // Auto-update based on delay
-        if last_update.elapsed() >= current_options.delay {
-            last_update = Instant::now();
-            update_display(&current_options)?;
+    fn apply(&mut self, flag: char, value: &str) -> Result<(), String> {
+        match flag {
+            'n' => {
+                let count = value
+                    .parse()
+                    .map_err(|_| format!("bad iterations argument '{value}'"))?;
+                self.iterations = Some(count);
+            }
+            'd' => {
+                let secs = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .ok_or_else(|| format!("bad delay interval '{value}'"))?;
+                self.delay = Duration::from_secs_f64(secs.min(f64::from(u32::MAX)));
+            }
+            'p' => {
+                for pid in value.split(',').filter(|pid| !pid.is_empty()) {
+                    let pid = pid.parse().map_err(|_| format!("bad pid '{pid}'"))?;
+                    self.pids.push(pid);
+                }
+            }
+            'u' => self.user = Some(value.to_string()),
+            _ => {
+                self.sort = match value.to_ascii_lowercase().as_str() {
+                    "%cpu" | "cpu" => SortKey::Cpu,
+                    "%mem" | "mem" => SortKey::Mem,
+                    "pid" => SortKey::Pid,
+                    "time" | "time+" => SortKey::Time,
+                    _ => return Err(format!("unrecognized field name '{value}'")),
+                };
+            }
         }
+        Ok(())
     }
 
-    Ok(())
-}
-
-#[derive(Debug)]
-enum KeyAction {
-    Quit,
-    Update,
-    Continue,
-}
-
-fn handle_key_event(key_event: KeyEvent, options: &mut TopOptions) -> ShellResult<KeyAction> {
-    match key_event.code {
-        KeyCode::Char('q') | KeyCode::Char('Q') => Ok(KeyAction::Quit),
-        KeyCode::Char(' ') => Ok(KeyAction::Update),
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            options.show_command_line = !options.show_command_line;
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('i') | KeyCode::Char('I') => {
-            options.show_idle = !options.show_idle;
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('H') => {
-            options.show_threads = !options.show_threads;
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('R') => {
-            options.reverse_sort = !options.reverse_sort;
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('P') => {
-            options.sort_field = "cpu".to_string();
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('M') => {
-            options.sort_field = "memory".to_string();
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('N') => {
-            options.sort_field = "pid".to_string();
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('T') => {
-            options.sort_field = "time".to_string();
-            Ok(KeyAction::Update)
-        }
-        KeyCode::Char('h') | KeyCode::Char('?') => {
-            show_help_screen()?;
-            Ok(KeyAction::Update)
-        }
-        _ => Ok(KeyAction::Continue),
+    fn shows(&self, process: &ProcessEntry) -> bool {
+        (self.pids.is_empty() || self.pids.contains(&process.pid))
+            && self.user.iter().all(|user| &process.user == user)
     }
 }
 
-fn update_display(options: &TopOptions) -> ShellResult<()> {
-    let system_info = collect_system_info()?;
-    let processes = collect_top_processes(options)?;
-
-    // Clear screen and move to top
-    execute!(
-        io::stdout(),
-        terminal::Clear(ClearType::All),
-        cursor::MoveTo(0, 0)
-    )
-    .map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to clear screen: {e}"),
-        )
-    })?;
-
-    display_interactive_output(&system_info, &processes, options)?;
-
-    Ok(())
+/// A question on the message line, with what has been typed so far
+#[derive(Debug, Clone, PartialEq)]
+enum Prompt {
+    Pid(String),
+    Signal(u32, String),
 }
 
-fn collect_system_info() -> ShellResult<SystemInfo> {
-    #[cfg(target_os = "linux")]
-    {
-        collect_linux_system_info()
-    }
-
-    #[cfg(not(target_os = "linux"))]
-    {
-        // Simplified system info for other platforms
-        Ok(SystemInfo {
-            uptime: Duration::from_secs(0),
-            load_avg: (0.0, 0.0, 0.0),
-            tasks_total: 0,
-            tasks_running: 0,
-            tasks_sleeping: 0,
-            tasks_stopped: 0,
-            tasks_zombie: 0,
-            cpu_user: 0.0,
-            cpu_system: 0.0,
-            cpu_nice: 0.0,
-            cpu_idle: 100.0,
-            cpu_wait: 0.0,
-            cpu_hi: 0.0,
-            cpu_si: 0.0,
-            cpu_steal: 0.0,
-            memory_total: 0,
-            memory_free: 0,
-            memory_used: 0,
-            memory_buffers: 0,
-            memory_cached: 0,
-            swap_total: 0,
-            swap_free: 0,
-            swap_used: 0,
-        })
-    }
+/// What a key asks the loop to do
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Redraw,
+    Quit,
+    Kill(u32, i32),
 }
 
-#[cfg(target_os = "linux")]
-fn collect_linux_system_info() -> ShellResult<SystemInfo> {
-    // Read /proc/uptime
-    let uptime = if let Ok(content) = fs::read_to_string("/proc/uptime") {
-        let parts: Vec<&str> = content.split_whitespace().collect();
-        if let Ok(uptime_secs) = parts[0].parse::<f64>() {
-            Duration::from_secs_f64(uptime_secs)
-        } else {
-            Duration::from_secs(0)
+/// What the keys change
+#[derive(Debug)]
+struct View {
+    sort: SortKey,
+    /// Smallest first instead of largest
+    reverse: bool,
+    full_command: bool,
+    hide_idle: bool,
+    prompt: Option<Prompt>,
+    /// The result of the last key, shown until the next one
+    message: Option<String>,
+}
+
+impl View {
+    fn new(options: &Options) -> Self {
+        View {
+            sort: options.sort,
+            reverse: false,
+            full_command: options.full_command,
+            hide_idle: options.hide_idle,
+            prompt: None,
+            message: None,
         }
-    } else {
-        Duration::from_secs(0)
-    };
+    }
 
-    // Read /proc/loadavg
-    let load_avg = if let Ok(content) = fs::read_to_string("/proc/loadavg") {
-        let parts: Vec<&str> = content.split_whitespace().collect();
-        if parts.len() >= 3 {
-            let load1 = parts[0].parse().unwrap_or(0.0);
-            let load5 = parts[1].parse().unwrap_or(0.0);
-            let load15 = parts[2].parse().unwrap_or(0.0);
-            (load1, load5, load15)
-        } else {
-            (0.0, 0.0, 0.0)
+    /// Handle a key; `first` is the process at the top of the table, which
+    /// the kill prompt offers
+    fn key(&mut self, key: KeyEvent, first: Option<u32>) -> Action {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Action::Quit;
         }
-    } else {
-        (0.0, 0.0, 0.0)
-    };
+        self.message = None;
+        if let Some(prompt) = self.prompt.take() {
+            return self.answer(prompt, key.code, first);
+        }
+        match key.code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Char('P') => self.sort = SortKey::Cpu,
+            KeyCode::Char('M') => self.sort = SortKey::Mem,
+            KeyCode::Char('N') => self.sort = SortKey::Pid,
+            KeyCode::Char('T') => self.sort = SortKey::Time,
+            KeyCode::Char('R') => self.reverse = !self.reverse,
+            KeyCode::Char('c') => self.full_command = !self.full_command,
+            KeyCode::Char('i') => self.hide_idle = !self.hide_idle,
+            KeyCode::Char('k') => self.prompt = Some(Prompt::Pid(String::new())),
+            _ => {}
+        }
+        Action::Redraw
+    }
 
-    // Read /proc/stat for CPU info
-    let (cpu_user, cpu_system, cpu_nice, cpu_idle, cpu_wait, cpu_hi, cpu_si, cpu_steal) =
-        if let Ok(content) = fs::read_to_string("/proc/stat") {
-            if let Some(cpu_line) = content.lines().next() {
-                let parts: Vec<&str> = cpu_line.split_whitespace().collect();
-                if parts.len() >= 8 {
-                    let user = parts[1].parse::<u64>().unwrap_or(0);
-                    let nice = parts[2].parse::<u64>().unwrap_or(0);
-                    let system = parts[3].parse::<u64>().unwrap_or(0);
-                    let idle = parts[4].parse::<u64>().unwrap_or(0);
-                    let iowait = parts[5].parse::<u64>().unwrap_or(0);
-                    let irq = parts[6].parse::<u64>().unwrap_or(0);
-                    let softirq = parts[7].parse::<u64>().unwrap_or(0);
-                    let steal = if parts.len() > 8 {
-                        parts[8].parse::<u64>().unwrap_or(0)
+    fn answer(&mut self, prompt: Prompt, code: KeyCode, first: Option<u32>) -> Action {
+        let (mut typed, next) = match prompt {
+            Prompt::Pid(typed) => (typed, None),
+            Prompt::Signal(pid, typed) => (typed, Some(pid)),
+        };
+        match code {
+            KeyCode::Esc => return Action::Redraw,
+            KeyCode::Enter => {
+                let Some(pid) = next else {
+                    let pid = if typed.is_empty() {
+                        first
                     } else {
-                        0
+                        typed.trim().parse().ok()
                     };
-
-                    let total = user + nice + system + idle + iowait + irq + softirq + steal;
-                    if total > 0 {
-                        (
-                            (user as f64 / total as f64) * 100.0,
-                            (system as f64 / total as f64) * 100.0,
-                            (nice as f64 / total as f64) * 100.0,
-                            (idle as f64 / total as f64) * 100.0,
-                            (iowait as f64 / total as f64) * 100.0,
-                            (irq as f64 / total as f64) * 100.0,
-                            (softirq as f64 / total as f64) * 100.0,
-                            (steal as f64 / total as f64) * 100.0,
-                        )
-                    } else {
-                        (0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 0.0)
+                    match pid {
+                        Some(pid) => self.prompt = Some(Prompt::Signal(pid, String::new())),
+                        None => self.message = Some(format!("Invalid PID '{typed}'")),
                     }
+                    return Action::Redraw;
+                };
+                let signal = if typed.is_empty() {
+                    Some(DEFAULT_SIGNAL)
                 } else {
-                    (0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 0.0)
-                }
-            } else {
-                (0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 0.0)
-            }
-        } else {
-            (0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 0.0)
-        };
-
-    // Read /proc/meminfo
-    let (memory_total, memory_free, memory_buffers, memory_cached, swap_total, swap_free) =
-        if let Ok(content) = fs::read_to_string("/proc/meminfo") {
-            let mut mem_total = 0;
-            let mut mem_free = 0;
-            let mut buffers = 0;
-            let mut cached = 0;
-            let mut swap_total = 0;
-            let mut swap_free = 0;
-
-            for line in content.lines() {
-                if line.starts_with("MemTotal:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        mem_total = value.parse::<u64>().unwrap_or(0) * 1024; // Convert KB to bytes
-                    }
-                } else if line.starts_with("MemFree:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        mem_free = value.parse::<u64>().unwrap_or(0) * 1024;
+                    nxsh_hal::signal::parse_signal(typed.trim())
+                };
+                return match signal {
+                    Some(signal) => Action::Kill(pid, signal),
+                    None => {
+                        self.message = Some(format!("Invalid signal '{typed}'"));
+                        Action::Redraw
                     }
-                } else if line.starts_with("Buffers:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        buffers = value.parse::<u64>().unwrap_or(0) * 1024;
-                    }
-                } else if line.starts_with("Cached:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        cached = value.parse::<u64>().unwrap_or(0) * 1024;
-                    }
-                } else if line.starts_with("SwapTotal:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        swap_total = value.parse::<u64>().unwrap_or(0) * 1024;
-                    }
-                } else if line.starts_with("SwapFree:") {
-                    if let Some(value) = line.split_whitespace().nth(1) {
-                        swap_free = value.parse::<u64>().unwrap_or(0) * 1024;
-                    }
-                }
+                };
             }
+            KeyCode::Backspace => {
+                typed.pop();
+            }
+            KeyCode::Char(ch) => typed.push(ch),
+            _ => {}
+        }
+        self.prompt = Some(match next {
+            Some(pid) => Prompt::Signal(pid, typed),
+            None => Prompt::Pid(typed),
+        });
+        Action::Redraw
+    }
 
-            (mem_total, mem_free, buffers, cached, swap_total, swap_free)
-        } else {
-            (0, 0, 0, 0, 0, 0)
-        };
-
-    let memory_used = memory_total.saturating_sub(memory_free + memory_buffers + memory_cached);
-    let swap_used = swap_total.saturating_sub(swap_free);
-
-    // Count tasks by reading /proc
-    let (tasks_total, tasks_running, tasks_sleeping, tasks_stopped, tasks_zombie) =
-        count_tasks().unwrap_or((0, 0, 0, 0, 0));
-
-    Ok(SystemInfo {
-        uptime,
-        load_avg,
-        tasks_total,
-        tasks_running,
-        tasks_sleeping,
-        tasks_stopped,
-        tasks_zombie,
-        cpu_user,
-        cpu_system,
-        cpu_nice,
-        cpu_idle,
-        cpu_wait,
-        cpu_hi,
-        cpu_si,
-        cpu_steal,
-        memory_total,
-        memory_free,
-        memory_used,
-        memory_buffers,
-        memory_cached,
-        swap_total,
-        swap_free,
-        swap_used,
-    })
-}
-
-#[cfg(target_os = "linux")]
-fn count_tasks() -> Result<(u32, u32, u32, u32, u32), Box<dyn std::error::Error>> {
-    let mut total = 0;
-    let mut running = 0;
-    let mut sleeping = 0;
-    let mut stopped = 0;
-    let mut zombie = 0;
-
-    let proc_dir = fs::read_dir("/proc")?;
-
-    for entry in proc_dir {
-        let entry = entry?;
-        let file_name = entry.file_name();
-        let name_str = file_name.to_string_lossy();
-
-        if let Ok(_pid) = name_str.parse::<u32>() {
-            total += 1;
+    /// The processes shown, in table order
+    fn rows<'a>(&self, options: &Options, snapshot: &'a ProcessSnapshot) -> Vec<&'a ProcessEntry> {
+        let mut rows: Vec<&ProcessEntry> = snapshot
+            .processes()
+            .iter()
+            .filter(|process| options.shows(process))
+            .filter(|process| {
+                !self.hide_idle
+                    || process.cpu_percent > 0.0
+                    || process.state == ProcessStatus::Running
+            })
+            .collect();
+        // Largest first, with ties left in PID order
+        match self.sort {
+            SortKey::Cpu => rows.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent)),
+            SortKey::Mem => rows.sort_by_key(|p| Reverse(p.rss)),
+            SortKey::Pid => rows.sort_by_key(|p| Reverse(p.pid)),
+            SortKey::Time => rows.sort_by_key(|p| Reverse(p.cpu_time)),
+        }
+        if self.reverse {
+            rows.reverse();
+        }
+        rows
+    }
 
-            let stat_path = format!("/proc/{}/stat", name_str);
-            if let Ok(content) = fs::read_to_string(&stat_path) {
-                let parts: Vec<&str> = content.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    match parts[2] {
-                        "R" => running += 1,
-                        "S" | "D" => sleeping += 1,
-                        "T" => stopped += 1,
-                        "Z" => zombie += 1,
-                        _ => {}
-                    }
-                }
+    /// The summary lines, the prompt or message line and the table, each
+    /// cut to `width` columns
+    fn frame(&self, options: &Options, snapshot: &ProcessSnapshot, width: usize) -> String {
+        let mut lines = summary(snapshot);
+        lines.push(match &self.prompt {
+            Some(Prompt::Pid(typed)) => {
+                let first = self
+                    .rows(options, snapshot)
+                    .first()
+                    .map(|process| process.pid);
+                let default =
+                    first.map_or_else(String::new, |pid| format!(" [default pid = {pid}]"));
+                format!("PID to signal/kill{default}: {typed}")
+            }
+            Some(Prompt::Signal(pid, typed)) => {
+                format!("Send pid {pid} signal [{DEFAULT_SIGNAL}/sigterm]: {typed}")
             }
+            None => self.message.clone().unwrap_or_default(),
+        });
+        lines.push(format!(
+            "{:>7} {:<8} {:>3} {:>7} {:>6} S {:>5} {:>5} {:>9} COMMAND",
+            "PID", "USER", "NI", "VIRT", "RES", "%CPU", "%MEM", "TIME+"
+        ));
+        for process in self.rows(options, snapshot) {
+            let user: String = process.user.chars().take(8).collect();
+            let command = if self.full_command {
+                &process.command_line
+            } else {
+                &process.name
+            };
+            lines.push(format!(
+                "{:>7} {:<8} {:>3} {:>7} {:>6} {} {:>5.1} {:>5.1} {:>9} {}",
+                process.pid,
+                user,
+                process.nice,
+                memory(process.vsz, 7),
+                memory(process.rss, 6),
+                process.state.code(),
+                process.cpu_percent,
+                snapshot.memory_percent(process),
+                cpu_time(process.cpu_time),
+                command
+            ));
+        }
+        let mut frame = String::new();
+        for line in lines {
+            frame.extend(line.chars().take(width));
+            frame.push('\n');
         }
+        frame
     }
-
-    Ok((total, running, sleeping, stopped, zombie))
 }
 
-fn collect_top_processes(options: &TopOptions) -> ShellResult<Vec<TopProcess>> {
-    let mut processes = Vec::new();
-
+/// The clock, uptime and load, task and memory lines
+fn summary(snapshot: &ProcessSnapshot) -> Vec<String> {
+    let mut first = format!("top - {}", chrono::Local::now().format("%H:%M:%S"));
     #[cfg(target_os = "linux")]
     {
-        let proc_dir = fs::read_dir("/proc").map_err(|e| {
-            ShellError::new(ErrorKind::IoError, format!("Cannot read /proc: {}", e))
-        })?;
-
-        for entry in proc_dir {
-            let entry = entry.map_err(|e| {
-                ShellError::new(
-                    ErrorKind::IoError,
-                    format!("Error reading /proc entry: {}", e),
-                )
-            })?;
-            let file_name = entry.file_name();
-            let name_str = file_name.to_string_lossy();
-
-            if let Ok(pid) = name_str.parse::<u32>() {
-                if let Ok(process) = read_top_process_info(pid) {
-                    if should_include_top_process(&process, options) {
-                        processes.push(process);
-                    }
-                }
-            }
+        use std::fmt::Write as _;
+        if let Ok(uptime) = crate::uptime::read_proc_uptime() {
+            let _ = write!(
+                first,
+                " up {}",
+                crate::uptime::format_uptime_duration(uptime)
+            );
+        }
+        if let Ok((one, five, fifteen)) = crate::uptime::read_proc_loadavg() {
+            let _ = write!(first, ",  load average: {one:.2}, {five:.2}, {fifteen:.2}");
         }
     }
 
-    // Sort processes
-    sort_top_processes(&mut processes, &options.sort_field, options.reverse_sort)?;
+    let processes = snapshot.processes();
+    let count = |state: ProcessStatus| processes.iter().filter(|p| p.state == state).count();
+    let tasks = format!(
+        "Tasks: {:>3} total, {:>3} running, {:>3} sleeping, {:>3} stopped, {:>3} zombie",
+        processes.len(),
+        count(ProcessStatus::Running),
+        count(ProcessStatus::Sleeping),
+        count(ProcessStatus::Stopped),
+        count(ProcessStatus::Zombie)
+    );
 
-    Ok(processes)
+    let mut lines = vec![first, tasks];
+    if let Ok(info) = MemoryManager::new().and_then(|memory| memory.memory_info()) {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        lines.push(format!(
+            "MiB Mem : {:>9.1} total, {:>9.1} free, {:>9.1} used",
+            mib(info.total_physical),
+            mib(info.available_physical),
+            mib(info.used_physical)
+        ));
+    }
+    lines
 }
 
-#[cfg(target_os = "linux")]
-fn read_top_process_info(pid: u32) -> Result<TopProcess, Box<dyn std::error::Error>> {
-    let stat_path = format!("/proc/{}/stat", pid);
-    let status_path = format!("/proc/{}/status", pid);
-    let cmdline_path = format!("/proc/{}/cmdline", pid);
-
-    // Read basic process info from stat
-    let stat_content = fs::read_to_string(&stat_path)?;
-    let stat_fields: Vec<&str> = stat_content.split_whitespace().collect();
-
-    if stat_fields.len() < 44 {
-        return Err("Invalid stat file format".into());
+/// An amount of memory in KiB, or in MiB or GiB when that does not fit in
+/// `width` columns
+fn memory(bytes: u64, width: usize) -> String {
+    let kib = bytes / 1024;
+    let plain = kib.to_string();
+    if plain.len() <= width {
+        return plain;
+    }
+    let mib = kib as f64 / 1024.0;
+    let scaled = format!("{mib:.1}m");
+    if scaled.len() <= width {
+        scaled
+    } else {
+        format!("{:.1}g", mib / 1024.0)
     }
+}
 
-    let ppid = stat_fields[3].parse::<u32>()?;
-    let priority = stat_fields[17].parse::<i32>()?;
-    let nice = stat_fields[18].parse::<i32>()?;
-    let num_threads = stat_fields[19].parse::<u32>()?;
-    let vsize = stat_fields[22].parse::<u64>()?;
-    let rss = stat_fields[23].parse::<u64>()? * 4096; // Convert pages to bytes
-    let state = stat_fields[2].to_string();
+/// CPU time as `M:SS.cc`
+fn cpu_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
 
-    // Read additional info from status
-    let mut uid = 0;
-    if let Ok(status_content) = fs::read_to_string(&status_path) {
-        for line in status_content.lines() {
-            if line.starts_with("Uid:") {
-                if let Some(uid_str) = line.split_whitespace().nth(1) {
-                    uid = uid_str.parse().unwrap_or(0);
-                }
-                break;
-            }
-        }
-    }
+/// Where the frames go
+trait Screen {
+    /// Columns available for a line
+    fn width(&self) -> usize;
 
-    // Get username
-    let user = get_username_by_uid(uid).unwrap_or_else(|| uid.to_string());
+    /// Show a frame in place of the previous one
+    fn draw(&mut self, frame: &str) -> io::Result<()>;
 
-    // Read command line
-    let command = if let Ok(cmdline_content) = fs::read(&cmdline_path) {
-        let cmdline_str = String::from_utf8_lossy(&cmdline_content);
-        let parts: Vec<&str> = cmdline_str.split('\0').filter(|s| !s.is_empty()).collect();
-        if !parts.is_empty() {
-            parts.join(" ")
-        } else {
-            format!("[{}]", pid)
-        }
-    } else {
-        format!("[{}]", pid)
-    };
+    /// Wait for up to `timeout` for a key
+    fn wait(&mut self, timeout: Duration) -> io::Result<Wait>;
+}
 
-    Ok(TopProcess {
-        pid,
-        ppid,
-        user,
-        priority,
-        nice,
-        virtual_memory: vsize,
-        resident_memory: rss,
-        shared_memory: 0, // Would need to read from statm
-        state,
-        cpu_percent: 0.0,                 // Would need multiple samples
-        memory_percent: 0.0,              // Would need total system memory
-        cpu_time: Duration::from_secs(0), // Would need to parse from stat
-        command,
-        threads: num_threads,
-    })
+/// How a wait ended
+enum Wait {
+    Timeout,
+    Key(KeyEvent),
+    /// The shell asked the command to stop
+    Stop,
 }
 
-#[cfg(target_os = "linux")]
-fn get_username_by_uid(uid: u32) -> Option<String> {
-    if let Ok(content) = fs::read_to_string("/etc/passwd") {
-        for line in content.lines() {
-            let fields: Vec<&str> = line.split(':').collect();
-            if fields.len() >= 3 {
-                if let Ok(file_uid) = fields[2].parse::<u32>() {
-                    if file_uid == uid {
-                        return Some(fields[0].to_string());
-                    }
+/// Draw frames until a key, the screen or `-n` ends it
+fn top(options: &Options, screen: &mut dyn Screen) -> Result<(), String> {
+    let mut view = View::new(options);
+    let start = ProcessSnapshot::capture().map_err(|e| e.to_string())?;
+    std::thread::sleep(FIRST_DELAY);
+    let mut snapshot = start.refresh().map_err(|e| e.to_string())?;
+    let mut frames = 0;
+    loop {
+        let frame = view.frame(options, &snapshot, screen.width());
+        screen.draw(&frame).map_err(|e| io_message(&e))?;
+        frames += 1;
+        if options.iterations.is_some_and(|count| frames >= count) {
+            return Ok(());
+        }
+        // Keys redraw at once; the processes are read again after the delay
+        let deadline = Instant::now() + options.delay;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            let key = match screen.wait(left).map_err(|e| io_message(&e))? {
+                Wait::Timeout => break,
+                Wait::Stop => return Ok(()),
+                Wait::Key(key) => key,
+            };
+            let first = view
+                .rows(options, &snapshot)
+                .first()
+                .map(|process| process.pid);
+            match view.key(key, first) {
+                Action::Quit => return Ok(()),
+                Action::Kill(pid, signal) => {
+                    view.message = Some(match execute_kill_target(pid, signal) {
+                        Ok(()) => format!("Sent signal {signal} to {pid}"),
+                        Err(e) => format!("Failed signal pid '{pid}' with '{signal}': {e}"),
+                    });
                 }
+                Action::Redraw => {}
             }
+            let frame = view.frame(options, &snapshot, screen.width());
+            screen.draw(&frame).map_err(|e| io_message(&e))?;
         }
+        snapshot = snapshot.refresh().map_err(|e| e.to_string())?;
     }
-    None
 }
 
-fn should_include_top_process(process: &TopProcess, options: &TopOptions) -> bool {
-    // Filter by user
-    if let Some(ref user) = options.filter_user {
-        if process.user != *user {
-            return false;
-        }
-    }
+/// The terminal, switched to its alternate screen in raw mode until dropped
+struct Terminal<W: Write> {
+    out: W,
+}
 
-    // Filter by PID
-    if let Some(pid) = options.filter_pid {
-        if process.pid != pid {
-            return false;
+impl<W: Write> Terminal<W> {
+    fn open(mut out: W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(out, EnterAlternateScreen, cursor::Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
         }
+        Ok(Terminal { out })
     }
+}
 
-    // Filter idle processes
-    if !options.show_idle && process.cpu_percent == 0.0 {
-        return false;
+impl<W: Write> Drop for Terminal<W> {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
     }
-
-    true
 }
 
-fn sort_top_processes(
-    processes: &mut [TopProcess],
-    sort_field: &str,
-    reverse: bool,
-) -> ShellResult<()> {
-    match sort_field {
-        "cpu" => processes.sort_by(|a, b| {
-            a.cpu_percent
-                .partial_cmp(&b.cpu_percent)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        }),
-        "memory" => processes.sort_by(|a, b| {
-            a.memory_percent
-                .partial_cmp(&b.memory_percent)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        }),
-        "pid" => processes.sort_by_key(|p| p.pid),
-        "time" => processes.sort_by_key(|p| p.cpu_time),
-        _ => {
-            return Err(ShellError::new(
-                ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                format!("Unknown sort field: {sort_field}"),
-            ))
-        }
+impl<W: Write> Screen for Terminal<W> {
+    fn width(&self) -> usize {
+        terminal::size().map_or(usize::MAX, |(columns, _)| usize::from(columns))
     }
 
-    if reverse {
-        processes.reverse();
+    fn draw(&mut self, frame: &str) -> io::Result<()> {
+        let rows = terminal::size().map_or(usize::MAX, |(_, rows)| usize::from(rows));
+        queue!(
+            self.out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        // Raw mode does not return the carriage on a newline, and a line
+        // past the bottom would scroll the summary away
+        for (index, line) in frame.lines().take(rows).enumerate() {
+            if index > 0 {
+                self.out.write_all(b"\r\n")?;
+            }
+            self.out.write_all(line.as_bytes())?;
+        }
+        self.out.flush()
     }
 
-    Ok(())
-}
-
-fn display_batch_output(
-    system_info: &SystemInfo,
-    processes: &[TopProcess],
-    options: &TopOptions,
-) -> ShellResult<()> {
-    display_system_header(system_info)?;
-    display_process_list(processes, options)?;
-    Ok(())
+    fn wait(&mut self, timeout: Duration) -> io::Result<Wait> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !event::poll(left)? {
+                return Ok(Wait::Timeout);
+            }
+            // Windows reports releases too, which would repeat each key
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    return Ok(Wait::Key(key));
+                }
+            }
+        }
+    }
 }
 
-fn display_interactive_output(
-    system_info: &SystemInfo,
-    processes: &[TopProcess],
-    options: &TopOptions,
-) -> ShellResult<()> {
-    display_system_header(system_info)?;
-    display_process_list(processes, options)?;
-
-    // Show status line
-    println!("\nPress 'h' for help, 'q' to quit");
-
-    Ok(())
+/// Frames printed one after another, a blank line apart, when there is no
+/// terminal or under `-b`
+struct Plain<W, F> {
+    out: W,
+    stopped: F,
 }
 
-fn display_system_header(system_info: &SystemInfo) -> ShellResult<()> {
-    // Top line - uptime and load
-    let uptime_str = format_uptime(system_info.uptime);
-    println!(
-        "top - {} up {}, load average: {:.2}, {:.2}, {:.2}",
-        format_current_time(),
-        uptime_str,
-        system_info.load_avg.0,
-        system_info.load_avg.1,
-        system_info.load_avg.2
-    );
-
-    // Tasks line
-    println!(
-        "Tasks: {} total, {} running, {} sleeping, {} stopped, {} zombie",
-        system_info.tasks_total,
-        system_info.tasks_running,
-        system_info.tasks_sleeping,
-        system_info.tasks_stopped,
-        system_info.tasks_zombie
-    );
-
-    // CPU line
-    println!(
-        "%Cpu(s): {:.1} us, {:.1} sy, {:.1} ni, {:.1} id, {:.1} wa, {:.1} hi, {:.1} si, {:.1} st",
-        system_info.cpu_user,
-        system_info.cpu_system,
-        system_info.cpu_nice,
-        system_info.cpu_idle,
-        system_info.cpu_wait,
-        system_info.cpu_hi,
-        system_info.cpu_si,
-        system_info.cpu_steal
-    );
-
-    // Memory lines
-    println!(
-        "MiB Mem : {:.1} total, {:.1} free, {:.1} used, {:.1} buff/cache",
-        system_info.memory_total as f64 / 1024.0 / 1024.0,
-        system_info.memory_free as f64 / 1024.0 / 1024.0,
-        system_info.memory_used as f64 / 1024.0 / 1024.0,
-        (system_info.memory_buffers + system_info.memory_cached) as f64 / 1024.0 / 1024.0
-    );
-
-    println!(
-        "MiB Swap: {:.1} total, {:.1} free, {:.1} used",
-        system_info.swap_total as f64 / 1024.0 / 1024.0,
-        system_info.swap_free as f64 / 1024.0 / 1024.0,
-        system_info.swap_used as f64 / 1024.0 / 1024.0
-    );
-
-    println!();
-
-    Ok(())
-}
+impl<W: Write, F: Fn() -> bool> Screen for Plain<W, F> {
+    fn width(&self) -> usize {
+        usize::MAX
+    }
 
-fn display_process_list(processes: &[TopProcess], options: &TopOptions) -> ShellResult<()> {
-    // Header
-    println!(
-        "{:>7} {:>9} {:>2} {:>2} {:>7} {:>7} {:>7} {:>1} {:>5} {:>5} {:>9} COMMAND",
-        "PID", "USER", "PR", "NI", "VIRT", "RES", "SHR", "S", "%CPU", "%MEM", "TIME+"
-    );
+    fn draw(&mut self, frame: &str) -> io::Result<()> {
+        self.out.write_all(frame.as_bytes())?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
 
-    // Process lines
-    for process in processes.iter().take(20) {
-        // Show top 20 processes
-        let command = if options.show_command_line {
-            &process.command
+    fn wait(&mut self, timeout: Duration) -> io::Result<Wait> {
+        Ok(if sleep_unless(timeout, &self.stopped) {
+            Wait::Stop
         } else {
-            process
-                .command
-                .split_whitespace()
-                .next()
-                .unwrap_or(&process.command)
-        };
-
-        println!(
-            "{:>7} {:>9} {:>2} {:>2} {:>7} {:>7} {:>7} {:>1} {:>5.1} {:>5.1} {:>9} {}",
-            process.pid,
-            truncate_string(&process.user, 9),
-            process.priority,
-            process.nice,
-            format_memory(process.virtual_memory),
-            format_memory(process.resident_memory),
-            format_memory(process.shared_memory),
-            process.state,
-            process.cpu_percent,
-            process.memory_percent,
-            format_time_duration(process.cpu_time),
-            truncate_string(command, 30)
-        );
+            Wait::Timeout
+        })
     }
-
-    Ok(())
-}
-
-fn show_help_screen() -> ShellResult<()> {
-    execute!(
-        io::stdout(),
-        terminal::Clear(ClearType::All),
-        cursor::MoveTo(0, 0)
-    )
-    .map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to clear screen: {e}"),
-        )
-    })?;
-
-    println!("Help for Interactive Commands - top version");
-    println!();
-    println!("Window 1:Def: Cumulative mode Off.  System: Delay 3.0 secs; Secure mode Off.");
-    println!();
-    println!("  Z,B,E,e   Global: 'Z' colors; 'B' bold; 'E'/'e' summary/task memory scale");
-    println!("  l,t,m     Toggle Summaries: 'l' load avg; 't' task/cpu stats; 'm' memory info");
-    println!("  0,1,2,3,I Toggle: '0' zeros; '1/2/3' cpus or numa node views; 'I' Irix mode");
-    println!("  f,F,X     Fields: 'f'/'F' add/remove/order/sort; 'X' increase fixed-width");
-    println!();
-    println!("  L,&,<,> . Locate: 'L'/'&' find/again; Move sort column: '<'/'>' left/right");
-    println!("  R,H,V,J . Toggle: 'R' Sort; 'H' Threads; 'V' Forest view; 'J' Num justify");
-    println!("  c,i,S,j . Toggle: 'c' Cmd name/line; 'i' Idle; 'S' Time; 'j' Str justify");
-    println!("  x,y     . Toggle highlights: 'x' sort field; 'y' running tasks");
-    println!("  z,b     . Toggle: 'z' color/mono; 'b' bold/reverse (only if 'x' or 'y')");
-    println!("  u,U,o,O . Filter by: 'u'/'U' effective/any user; 'o'/'O' other criteria");
-    println!("  n,#,^O  . Set: 'n'/'#' max tasks displayed; '^O' other output options");
-    println!("  C,...   . Toggle scroll coordinates msg for: up,down,left,right,home,end");
-    println!();
-    println!("  k,r       Manipulate tasks: 'k' kill; 'r' renice");
-    println!("  d or s    Set update interval");
-    println!("  W,Y       Write configuration file 'W'; Inspect other output 'Y'");
-    println!("  q         Quit");
-    println!("          ( commands shown with '.' require a visible task display window )");
-    println!();
-    println!("Press any key to continue...");
-
-    // Wait for key press
-    event::read().map_err(|e| {
-        ShellError::new(
-            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-            format!("Failed to read key: {e}"),
-        )
-    })?;
-
-    Ok(())
 }
 
-fn format_uptime(uptime: Duration) -> String {
-    let total_seconds = uptime.as_secs();
-    let days = total_seconds / 86400;
-    let hours = (total_seconds % 86400) / 3600;
-    let minutes = (total_seconds % 3600) / 60;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if days > 0 {
-        format!("{days} days, {hours}:{minutes:02}")
-    } else if hours > 0 {
-        format!("{hours}:{minutes:02}")
-    } else {
-        format!("{minutes} min")
-    }
-}
-
-fn format_current_time() -> String {
-    // Simplified time formatting
-    match SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(duration) => {
-            let timestamp = duration.as_secs();
-            let hours = (timestamp % 86400) / 3600;
-            let minutes = (timestamp % 3600) / 60;
-            let seconds = timestamp % 60;
-            format!("{hours:02}:{minutes:02}:{seconds:02}")
+    fn press(view: &mut View, keys: &str) -> Action {
+        let mut action = Action::Redraw;
+        for ch in keys.chars() {
+            let code = match ch {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                _ => KeyCode::Char(ch),
+            };
+            action = view.key(KeyEvent::new(code, KeyModifiers::NONE), Some(42));
         }
-        Err(_) => "??:??:??".to_string(),
+        action
     }
-}
 
-fn format_memory(bytes: u64) -> String {
-    if bytes >= 1024 * 1024 * 1024 {
-        format!("{:.1}g", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
-    } else if bytes >= 1024 * 1024 {
-        format!("{:.1}m", bytes as f64 / (1024.0 * 1024.0))
-    } else if bytes >= 1024 {
-        format!("{}k", bytes / 1024)
-    } else {
-        format!("{bytes}")
+    fn view() -> View {
+        View::new(&Options::parse(&[]).unwrap())
     }
-}
-
-fn format_time_duration(duration: Duration) -> String {
-    let total_seconds = duration.as_secs();
-    let minutes = total_seconds / 60;
-    let seconds = total_seconds % 60;
-    format!("{minutes}:{seconds:02}")
-}
 
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}+", &s[..max_len.saturating_sub(1)])
+    #[test]
+    fn keys_change_the_order_and_columns() {
+        let mut view = view();
+        assert_eq!(press(&mut view, "MRc"), Action::Redraw);
+        assert_eq!(view.sort, SortKey::Mem);
+        assert!(view.reverse && view.full_command);
+        assert_eq!(press(&mut view, "q"), Action::Quit);
     }
-}
 
-// CLI entry point function
-pub fn top_cli(_args: &[String]) -> anyhow::Result<()> {
-    let options = TopOptions::default();
-
-    if options.batch_mode {
-        if let Err(e) = run_batch_mode(&options) {
-            return Err(anyhow::anyhow!("top error: {}", e));
-        }
-    } else if let Err(e) = run_interactive_mode(&options) {
-        return Err(anyhow::anyhow!("top error: {}", e));
+    #[test]
+    fn kill_prompt_offers_defaults_and_cancels() {
+        let mut view = view();
+        assert_eq!(press(&mut view, "k\n\n"), Action::Kill(42, 15));
+        assert_eq!(press(&mut view, "k7\n9\n"), Action::Kill(7, 9));
+        assert_eq!(press(&mut view, "k7\nhup\n"), Action::Kill(7, 1));
+        assert_eq!(press(&mut view, "k7\x1b"), Action::Redraw);
+        assert!(view.prompt.is_none());
+        assert_eq!(press(&mut view, "kx\n"), Action::Redraw);
+        assert_eq!(view.message.as_deref(), Some("Invalid PID 'x'"));
     }
 
-    Ok(())
-}
+    #[test]
+    fn formats_memory_and_time() {
+        assert_eq!(memory(2048 * 1024, 6), "2048");
+        assert_eq!(memory(2 * 1024 * 1024 * 1024, 6), "2.0g");
+        assert_eq!(cpu_time(Duration::from_millis(123_456)), "2:03.45");
+    }
 
-pub fn execute(
-    _args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    println!("top: Command not yet implemented");
-    Ok(0)
+    #[test]
+    fn huge_delay_is_clamped() {
+        let options = Options::parse(&["-d".to_string(), "1e300".to_string()]).unwrap();
+        assert_eq!(options.delay, Duration::from_secs(u64::from(u32::MAX)));
+    }
 }
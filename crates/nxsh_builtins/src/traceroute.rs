@@ -1,29 +1,531 @@
-//! `traceroute` builtin  Edisplay route to host.
+//! `traceroute` and `tracert` builtins - show the routers on the way to a host
 //!
-//! Delegates to system `traceroute` (Unix) or `tracert.exe` (Windows).
-//! All arguments are forwarded unchanged for maximum compatibility.
+//! Syntax:
+//!   traceroute [-46InU] [-f FIRST] [-m MAX] [-q PROBES] [-w SECS] [-p PORT] HOST
+//!   tracert [-d] [-4 | -6] [-h MAX] [-w MSECS] HOST
+//!
+//! Sends probes to HOST with a time to live that starts at FIRST (1 by
+//! default) and grows by one for each hop, and prints a line per hop with
+//! the routers whose time exceeded reports came back and the round trip
+//! time of every probe, or `*` for a probe nothing answered. The trace ends
+//! at the hop where HOST answers or is reported unreachable, or after MAX
+//! hops (30 by default). Routers show by name and address unless `-n` asks
+//! for addresses only, and a report that HOST is unreachable is marked
+//! `!N`, `!H`, `!P` or `!X` for a network, host, protocol or prohibited
+//! destination.
+//!
+//! Options:
+//!   -4, -6      use IPv4 or IPv6 only
+//!   -I          probe with ICMP echo requests, the default
+//!   -U          probe with UDP datagrams to PORT and up, on Linux only
+//!   -n          print addresses without looking up their names
+//!   -f FIRST    the time to live of the first hop probed
+//!   -m MAX      the largest time to live probed
+//!   -q PROBES   probes sent to each hop (3 by default)
+//!   -w SECS     seconds to wait for each probe's answer (3 by default)
+//!   -p PORT     the port of the first UDP probe (33434 by default)
+//!
+//! `tracert` takes the options of its Windows namesake instead: `-d` prints
+//! addresses only, `-h` sets MAX and `-w` the wait in milliseconds.
+//!
+//! On an interactive terminal each hop's line fills in as its probes are
+//! answered and Ctrl-C stops the trace. Otherwise the trace stops at the
+//! shell's time limit.
+//!
+//! The exit status is 0 when the trace ran, whether or not it reached HOST,
+//! and 1 for bad options, an unknown HOST or a probe that could not be sent.
+
+use crate::common::dns::{self, QueryOptions, RecordData, RecordType};
+use crate::common::icmp::{resolve, Family, ProbeKind, Prober, ReplyKind, Unreachable};
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crate::ping::{positive, Plain, Screen, Terminal};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// The largest time to live probed without `-m`
+const DEFAULT_MAX_TTL: u8 = 30;
+
+/// Probes per hop without `-q`
+const DEFAULT_PROBES: u64 = 3;
+
+/// Seconds to wait for an answer without `-w`
+const DEFAULT_WAIT: f64 = 3.0;
+
+/// The first UDP port without `-p`
+const DEFAULT_PORT: u16 = 33434;
+
+/// Bytes of data in each probe
+const PROBE_SIZE: usize = 32;
+
+/// The `traceroute` builtin command implementation
+pub struct TracerouteCommand;
+
+impl Builtin for TracerouteCommand {
+    fn name(&self) -> &'static str {
+        "traceroute"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Show the routers on the way to a host"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send probes with a growing time to live and print the routers that report \
+         them expired, hop by hop, with the round trip time of each probe."
+    }
+
+    fn usage(&self) -> &'static str {
+        "traceroute [-46InU] [-f FIRST] [-m MAX] [-q PROBES] [-w SECS] [-p PORT] HOST"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the routers on the way to a host. Use 'traceroute -n example.com' to skip \
+         name lookups or 'traceroute -m 10 -q 1 ::1' for a short IPv6 trace."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        Ok(run_in(Flavor::Traceroute, ctx, args))
+    }
+}
+
+/// The `tracert` builtin command implementation
+pub struct TracertCommand;
+
+impl Builtin for TracertCommand {
+    fn name(&self) -> &'static str {
+        "tracert"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Show the routers on the way to a host"
+    }
+
+    fn description(&self) -> &'static str {
+        "traceroute with the options of the Windows command: -d for addresses only, \
+         -h for the hop limit and -w for the wait in milliseconds."
+    }
+
+    fn usage(&self) -> &'static str {
+        "tracert [-d] [-4 | -6] [-h MAX] [-w MSECS] HOST"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the routers on the way to a host. Use 'tracert -d -h 15 example.com' for \
+         at most 15 hops without name lookups."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        Ok(run_in(Flavor::Tracert, ctx, args))
+    }
+}
+
+/// Run traceroute for the legacy dispatcher, writing to the process's
+/// terminal or standard output
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Traceroute, args)
+}
+
+/// Run tracert for the legacy dispatcher
+pub fn execute_tracert(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    run_legacy(Flavor::Tracert, args)
+}
+
+/// Which command's options are taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    Traceroute,
+    Tracert,
+}
 
-use anyhow::{anyhow, Result};
-use std::process::Command;
-use which::which;
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Traceroute => "traceroute",
+            Flavor::Tracert => "tracert",
+        }
+    }
+}
 
-pub fn traceroute_cli(args: &[String]) -> Result<()> {
-    let candidates = if cfg!(windows) {
-        vec!["tracert.exe", "tracert"]
+fn run_in(flavor: Flavor, ctx: &mut ShellContext, args: &[String]) -> ExecutionResult {
+    let options = match Options::parse(flavor, args) {
+        Ok(options) => options,
+        Err(message) => {
+            return ExecutionResult::failure(1)
+                .with_error(format!("{}: {message}\n", flavor.name()).into_bytes())
+        }
+    };
+    let (result, stdout) = if ctx.is_interactive() && io::stdout().is_terminal() {
+        let result = Terminal::open(&mut *ctx.stdout)
+            .map_err(|e| io_message(&e))
+            .and_then(|mut screen| trace(&options, &mut screen));
+        (result, Vec::new())
     } else {
-        vec!["traceroute", "tracepath"]
+        let mut screen = Plain {
+            out: Vec::new(),
+            stopped: || ctx.is_timed_out(),
+        };
+        let result = trace(&options, &mut screen);
+        (result, screen.out)
+    };
+    let (status, stderr) = match result {
+        Ok(()) => (0, String::new()),
+        Err(message) => (1, format!("{}: {message}\n", flavor.name())),
+    };
+    ExecutionResult::success(status)
+        .with_output(stdout)
+        .with_error(stderr.into_bytes())
+}
+
+fn run_legacy(flavor: Flavor, args: &[String]) -> BuiltinResult<i32> {
+    let result = Options::parse(flavor, args).and_then(|options| {
+        if io::stdout().is_terminal() {
+            Terminal::open(io::stdout())
+                .map_err(|e| io_message(&e))
+                .and_then(|mut screen| trace(&options, &mut screen))
+        } else {
+            let mut screen = Plain {
+                out: io::stdout(),
+                stopped: || false,
+            };
+            trace(&options, &mut screen)
+        }
+    });
+    match result {
+        Ok(()) => Ok(0),
+        Err(message) => {
+            writeln!(io::stderr(), "{}: {message}", flavor.name())?;
+            Ok(1)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    host: String,
+    family: Family,
+    kind: ProbeKind,
+    numeric: bool,
+    first_ttl: u8,
+    max_ttl: u8,
+    probes: u64,
+    wait: Duration,
+}
+
+impl Options {
+    fn parse(flavor: Flavor, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            host: String::new(),
+            family: Family::Any,
+            kind: ProbeKind::Echo,
+            numeric: false,
+            first_ttl: 1,
+            max_ttl: DEFAULT_MAX_TTL,
+            probes: DEFAULT_PROBES,
+            wait: Duration::from_secs_f64(DEFAULT_WAIT),
+        };
+        let tracert = flavor == Flavor::Tracert;
+        let valued = if tracert { "hw" } else { "fmqwp" };
+        let mut udp = false;
+        let mut port = DEFAULT_PORT;
+        let mut hosts = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    hosts.extend(iter.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            '4' => options.family = Family::V4,
+                            '6' => options.family = Family::V6,
+                            'd' if tracert => options.numeric = true,
+                            'n' if !tracert => options.numeric = true,
+                            'I' if !tracert => udp = false,
+                            'U' if !tracert => udp = true,
+                            flag if valued.contains(flag) => {
+                                let attached = &short[2 + at..];
+                                let value = match attached {
+                                    "" => iter.next().map(String::as_str).ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?,
+                                    _ => attached,
+                                };
+                                match flag {
+                                    'f' => options.first_ttl = number(value, 255, "first hop")?,
+                                    'm' | 'h' => options.max_ttl = number(value, 255, "max hops")?,
+                                    'q' => options.probes = number(value, 10, "number of probes")?,
+                                    'p' => port = number(value, 65535, "port")?,
+                                    _ if tracert => {
+                                        let millis = number(value, u64::MAX, "timeout")?;
+                                        options.wait = Duration::from_millis(millis);
+                                    }
+                                    _ => options.wait = positive(value, "wait")?,
+                                }
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => hosts.push(arg.clone()),
+            }
+        }
+        options.host = match hosts.as_slice() {
+            [host] => host.clone(),
+            [] => return Err("missing host operand".to_string()),
+            [_, extra, ..] => return Err(format!("extra operand '{extra}'")),
+        };
+        if options.first_ttl > options.max_ttl {
+            return Err(format!(
+                "first hop {} is past the last hop {}",
+                options.first_ttl, options.max_ttl
+            ));
+        }
+        if udp {
+            options.kind = ProbeKind::Udp { port };
+        }
+        Ok(options)
+    }
+}
+
+/// A number from 1 to `max`
+fn number<T: TryFrom<u64>>(text: &str, max: u64, what: &str) -> Result<T, String> {
+    match text.parse::<u64>() {
+        Ok(value) if (1..=max).contains(&value) => {
+            T::try_from(value).map_err(|_| format!("invalid {what} '{text}'"))
+        }
+        _ => Err(format!("invalid {what} '{text}'")),
+    }
+}
+
+/// Resolve the host and open the socket, then probe hop by hop
+fn trace(options: &Options, screen: &mut dyn Screen) -> Result<(), String> {
+    let target = resolve(&options.host, options.family)?;
+    let mut prober =
+        Prober::open(target, options.kind).map_err(|e| format!("socket: {}", io_message(&e)))?;
+    let header = if target.is_ipv6() { 40 } else { 20 };
+    let first = format!(
+        "traceroute to {} ({target}), {} hops max, {} byte packets",
+        options.host,
+        options.max_ttl,
+        header + 8 + PROBE_SIZE
+    );
+    let mut names = Names {
+        servers: if options.numeric {
+            Vec::new()
+        } else {
+            dns::system_servers()
+        },
+        numeric: options.numeric,
+        seen: HashMap::new(),
     };
+    hops(options, &first, &mut prober, &mut names, screen).map_err(|e| io_message(&e))
+}
 
-    for bin in candidates {
-        if let Ok(path) = which(bin) {
-            let status = Command::new(path)
-                .args(args)
-                .status()
-                .map_err(|e| anyhow!("traceroute: failed to launch backend: {e}"))?;
-            std::process::exit(status.code().unwrap_or(1));
+/// Print a line per hop until HOST answers, the last hop is probed or the
+/// screen says to stop
+fn hops(
+    options: &Options,
+    first: &str,
+    prober: &mut Prober,
+    names: &mut Names,
+    screen: &mut dyn Screen,
+) -> io::Result<()> {
+    screen.line(first)?;
+    let mut seq: u16 = 0;
+    for ttl in options.first_ttl..=options.max_ttl {
+        let mut line = format!("{ttl:>2} ");
+        let mut last: Option<IpAddr> = None;
+        let mut done = false;
+        for _ in 0..options.probes {
+            seq = seq.wrapping_add(1);
+            let reply = prober.probe(seq, Some(ttl), PROBE_SIZE, options.wait, &mut || {
+                screen.wait(Duration::ZERO).unwrap_or(true)
+            });
+            match reply {
+                Ok(Some(reply)) => {
+                    if last != Some(reply.from) {
+                        let _ = write!(line, " {}", names.show(reply.from));
+                        last = Some(reply.from);
+                    }
+                    let _ = write!(line, "  {:.3} ms", reply.rtt.as_secs_f64() * 1000.0);
+                    if let ReplyKind::Unreachable(reason) = reply.kind {
+                        if let Some(mark) = mark(reason) {
+                            let _ = write!(line, " {mark}");
+                        }
+                    }
+                    // The target answered, or someone said it never will
+                    done |= reply.kind != ReplyKind::TimeExceeded;
+                }
+                Ok(None) => line.push_str(" *"),
+                Err(e) => {
+                    screen.line(&line)?;
+                    return Err(e);
+                }
+            }
+            if screen.wait(Duration::ZERO)? {
+                return screen.line(&line);
+            }
+            screen.status(&line)?;
         }
+        screen.line(&line)?;
+        if done {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The mark of a probe that drew an unreachable report. A port unreachable
+/// is how a UDP probe's target answers, and goes unmarked.
+fn mark(reason: Unreachable) -> Option<String> {
+    match reason {
+        Unreachable::Network => Some("!N".to_string()),
+        Unreachable::Host => Some("!H".to_string()),
+        Unreachable::Protocol => Some("!P".to_string()),
+        Unreachable::Port => None,
+        Unreachable::Prohibited => Some("!X".to_string()),
+        Unreachable::Other(code) => Some(format!("!<{code}>")),
+    }
+}
+
+/// The names of the routers in the trace, each looked up once
+struct Names {
+    servers: Vec<IpAddr>,
+    numeric: bool,
+    seen: HashMap<IpAddr, Option<String>>,
+}
+
+impl Names {
+    /// An address as the trace shows it: alone under `-n`, and otherwise
+    /// after its name, or after itself when it has none
+    fn show(&mut self, address: IpAddr) -> String {
+        if self.numeric {
+            return address.to_string();
+        }
+        let servers = &self.servers;
+        let name = self
+            .seen
+            .entry(address)
+            .or_insert_with(|| lookup(servers, address));
+        match name {
+            Some(name) => format!("{name} ({address})"),
+            None => format!("{address} ({address})"),
+        }
+    }
+}
+
+/// The name the first name server that answers gives `address`
+fn lookup(servers: &[IpAddr], address: IpAddr) -> Option<String> {
+    let options = QueryOptions {
+        timeout: Duration::from_secs(1),
+        tries: 1,
+        tcp: false,
+    };
+    let name = dns::reverse_name(address);
+    for server in servers {
+        let Ok(reply) = dns::query(
+            SocketAddr::new(*server, 53),
+            &name,
+            RecordType::PTR,
+            &options,
+        ) else {
+            continue;
+        };
+        return reply
+            .response
+            .answers
+            .iter()
+            .find_map(|record| match &record.data {
+                RecordData::Name(name) if record.rtype == RecordType::PTR => {
+                    Some(name.trim_end_matches('.').to_string())
+                }
+                _ => None,
+            });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(flavor: Flavor, list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(flavor, &args)
+    }
+
+    #[test]
+    fn parses_traceroute_options() {
+        let parsed = options(
+            Flavor::Traceroute,
+            &["-nU", "-p", "40000", "-m5", "-q1", "host"],
+        )
+        .unwrap();
+        assert!(parsed.numeric);
+        assert_eq!(parsed.kind, ProbeKind::Udp { port: 40000 });
+        assert_eq!((parsed.first_ttl, parsed.max_ttl, parsed.probes), (1, 5, 1));
+        assert_eq!(parsed.host, "host");
+
+        let parsed = options(Flavor::Traceroute, &["-6", "-w", "0.5", "-f", "3", "::1"]).unwrap();
+        assert_eq!(parsed.family, Family::V6);
+        assert_eq!(parsed.kind, ProbeKind::Echo);
+        assert_eq!(parsed.wait, Duration::from_millis(500));
+        assert_eq!(parsed.first_ttl, 3);
+
+        assert_eq!(
+            options(Flavor::Traceroute, &["-f", "9", "-m", "8", "h"]).unwrap_err(),
+            "first hop 9 is past the last hop 8"
+        );
+        assert_eq!(
+            options(Flavor::Traceroute, &["-m", "256", "h"]).unwrap_err(),
+            "invalid max hops '256'"
+        );
+        assert_eq!(
+            options(Flavor::Traceroute, &["-d", "h"]).unwrap_err(),
+            "invalid option -- 'd'"
+        );
     }
 
-    Err(anyhow!("traceroute: no compatible backend found in PATH"))
-} 
+    #[test]
+    fn parses_tracert_options() {
+        let parsed = options(Flavor::Tracert, &["-d", "-h", "15", "-w", "750", "host"]).unwrap();
+        assert!(parsed.numeric);
+        assert_eq!(parsed.max_ttl, 15);
+        assert_eq!(parsed.wait, Duration::from_millis(750));
+        assert_eq!(
+            options(Flavor::Tracert, &["-n", "host"]).unwrap_err(),
+            "invalid option -- 'n'"
+        );
+        assert_eq!(
+            options(Flavor::Tracert, &[]).unwrap_err(),
+            "missing host operand"
+        );
+    }
 
+    #[test]
+    fn marks_unreachable_reports() {
+        assert_eq!(mark(Unreachable::Host).as_deref(), Some("!H"));
+        assert_eq!(mark(Unreachable::Port), None);
+        assert_eq!(mark(Unreachable::Other(7)).as_deref(), Some("!<7>"));
+        let mut names = Names {
+            servers: Vec::new(),
+            numeric: false,
+            seen: HashMap::new(),
+        };
+        let address: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(names.show(address), "192.0.2.1 (192.0.2.1)");
+        names.numeric = true;
+        assert_eq!(names.show(address), "192.0.2.1");
+    }
+}
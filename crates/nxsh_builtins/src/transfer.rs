@@ -0,0 +1,78 @@
+//! The progress line of the copies `cp` and `mv` make
+//!
+//! [`Transfer`] words one line for the whole copy: the file being copied
+//! and how far through it is, the bytes copied of the total, the rate so
+//! far and the time left at that rate, followed by a [`ProgressBar`] of
+//! the total. The line starts with a carriage return so each one can be
+//! written over the last.
+
+use crate::df::human_size;
+use nxsh_ui::ProgressBar;
+use std::time::{Duration, Instant};
+
+/// How often the line is redrawn
+pub(crate) const REDRAW: Duration = Duration::from_millis(100);
+
+/// Copies of at least this many bytes show their progress on a terminal
+/// without being asked to
+pub(crate) const LARGE: u64 = 256 << 20;
+
+/// The progress of one copy of `total` bytes
+pub(crate) struct Transfer {
+    bar: ProgressBar,
+    total: u64,
+    started: Instant,
+}
+
+impl Transfer {
+    pub(crate) fn new(total: u64) -> Self {
+        Transfer {
+            bar: ProgressBar::new(total),
+            total,
+            started: Instant::now(),
+        }
+    }
+
+    /// The line for `done` bytes copied in all, `file_done` of them of the
+    /// `file_size` bytes of `file`
+    pub(crate) fn line(&mut self, done: u64, file: &str, file_done: u64, file_size: u64) -> String {
+        let percent =
+            |part: u64, whole: u64| (part.min(whole) * 100).checked_div(whole).unwrap_or(100);
+        let seconds = self.started.elapsed().as_secs_f64();
+        let rate = if seconds > 0.0 {
+            (done as f64 / seconds) as u64
+        } else {
+            0
+        };
+        let eta = match rate {
+            0 => "--:--".to_string(),
+            rate => {
+                let left = self.total.saturating_sub(done) / rate;
+                format!("{}:{:02}", left / 60, left % 60)
+            }
+        };
+        self.bar.set_position(done);
+        self.bar.set_message(format!(
+            "{file} {}% {}/{} {}/s ETA {eta}",
+            percent(file_done, file_size),
+            human_size(done, 1024),
+            human_size(self.total, 1024),
+            human_size(rate, 1024),
+        ));
+        format!("\r{}\x1b[K", self.bar.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_file_and_total_progress() {
+        let mut transfer = Transfer::new(4 << 20);
+        let line = transfer.line(1 << 20, "big.iso", 1 << 20, 2 << 20);
+        assert!(line.starts_with("\rbig.iso 50% 1.0M/4.0M "), "{line:?}");
+        assert!(line.contains("/s ETA "), "{line:?}");
+        assert!(line.ends_with("] 25%\x1b[K"), "{line:?}");
+    }
+}
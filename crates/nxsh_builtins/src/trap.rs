@@ -1,99 +1,172 @@
-//! `trap` builtin  Eset or clear signal handlers.
-//! Syntax examples:
-//!   trap CMD SIGNALS...
-//!   trap -l          # list signals
-//!   trap -p          # print current traps
-//!   trap - SIG       # reset default handler
+//! `trap` builtin - run commands when the shell receives signals
 //!
-//! For this minimal implementation we support listing signals and setting a handler that
-//! prints the received signal name and executes a shell command via `Executor::run`.
-
-use anyhow::{anyhow, Result};
-use once_cell::sync::Lazy;
-use signal_hook::consts::*;
-use std::collections::HashMap;
-use std::sync::Mutex;
+//! Syntax:
+//!   trap 'CMD' COND...   # run CMD on each condition
+//!   trap '' COND...      # ignore the signal(s)
+//!   trap - COND...       # restore default behaviour
+//!   trap COND            # same as `trap - COND` (POSIX legacy form)
+//!   trap -p [COND...]    # print traps in a re-usable form
+//!   trap -l              # list signal names and numbers
+//!
+//! Conditions are signal names (`INT`, `SIGTERM`), signal numbers, or one of
+//! the pseudo-signals `EXIT` (`0`), `ERR`, `DEBUG` and `RETURN`. The registry
+//! lives in `ShellContext::traps`; the executor runs the actions.
+
+use crate::common::{shell_quote, usage_error};
 use nxsh_core::context::ShellContext;
+use nxsh_core::trap::TrapCondition;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::signal::ShellSignal;
 
-static HANDLERS: Lazy<Mutex<HashMap<i32, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// The `trap` builtin command implementation
+pub struct TrapCommand;
 
-pub fn trap_cli(args: &[String], _ctx: &mut ShellContext) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("trap: missing arguments"));
+impl Builtin for TrapCommand {
+    fn name(&self) -> &'static str {
+        "trap"
     }
-    if args[0] == "-l" {
-        list_signals();
-        return Ok(());
+
+    fn synopsis(&self) -> &'static str {
+        "Trap signals and other events"
     }
-    if args[0] == "-p" {
-        let h = HANDLERS.lock()
-            .map_err(|e| anyhow::anyhow!("Failed to acquire trap handlers lock: {}", e))?;
-        for (sig, cmd) in h.iter() {
-            println!("trap -- '{cmd}' {sig}");
-        }
-        return Ok(());
+
+    fn description(&self) -> &'static str {
+        "Run ACTION when the shell receives one of the listed signals, exits (EXIT), \
+         a command fails (ERR) or before each simple command (DEBUG)."
+    }
+
+    fn usage(&self) -> &'static str {
+        "trap [-lp] [[ACTION] CONDITION ...]"
     }
 
-    let cmd = &args[0];
-    let signals: Vec<i32> = args[1..]
-        .iter()
-        .map(|s| parse_signal(s))
-        .collect::<Result<_>>()?;
+    fn help(&self) -> &'static str {
+        "Trap signals and other events. Use 'trap -l' to list signal names."
+    }
 
-    for sig in signals {
-        set_handler(sig, cmd.clone())?;
+    fn affects_shell_state(&self) -> bool {
+        true // trap modifies the shell's trap table
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut print = false;
+        let mut rest = args;
+        while let Some(flag) = rest.first() {
+            match flag.as_str() {
+                "-l" => return Ok(list_signals()),
+                "-p" => print = true,
+                "--" => {
+                    rest = &rest[1..];
+                    break;
+                }
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Ok(usage_error(&format!("trap: {f}: invalid option")));
+                }
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+
+        if print || rest.is_empty() {
+            return Ok(self.print_traps(ctx, rest));
+        }
+
+        // A lone condition resets it; otherwise the first word is the action
+        let (action, conditions) = if rest.len() == 1 {
+            match TrapCondition::parse(&rest[0]) {
+                Some(_) => (None, rest),
+                None => {
+                    return Ok(usage_error(
+                        "trap: usage: trap [-lp] [[ACTION] CONDITION ...]",
+                    ))
+                }
+            }
+        } else if rest[0] == "-" {
+            (None, &rest[1..])
+        } else {
+            (Some(rest[0].as_str()), &rest[1..])
+        };
+
+        let mut stderr = String::new();
+        let mut traps = match ctx.traps.write() {
+            Ok(traps) => traps,
+            Err(_) => return Ok(usage_error("trap: trap table unavailable")),
+        };
+        for spec in conditions {
+            let Some(condition) = TrapCondition::parse(spec) else {
+                stderr.push_str(&format!("trap: {spec}: invalid signal specification\n"));
+                continue;
+            };
+            let outcome = match action {
+                Some(action) => traps.set(condition, action),
+                None => traps.reset(condition),
+            };
+            if let Err(e) = outcome {
+                stderr.push_str(&format!("{e}\n"));
+            }
+        }
+
+        if stderr.is_empty() {
+            Ok(ExecutionResult::success(0))
+        } else {
+            Ok(ExecutionResult::failure(1).with_error(stderr.into_bytes()))
+        }
     }
-    Ok(())
 }
 
-// Cross-platform signal constants
-#[cfg(unix)]
-const SIGHUP: i32 = signal_hook::consts::SIGHUP;
-#[cfg(unix)]
-const SIGUSR1: i32 = signal_hook::consts::SIGUSR1;
-#[cfg(unix)]
-const SIGUSR2: i32 = signal_hook::consts::SIGUSR2;
-
-#[cfg(windows)]
-const SIGHUP: i32 = 1;  // Simulate signals on Windows
-#[cfg(windows)]
-const SIGUSR1: i32 = 10;
-#[cfg(windows)]
-const SIGUSR2: i32 = 12;
-
-fn parse_signal(s: &str) -> Result<i32> {
-    if let Ok(num) = s.parse::<i32>() { return Ok(num); }
-    match s.trim_start_matches("SIG").to_uppercase().as_str() {
-        "INT" => Ok(SIGINT),
-        "TERM" => Ok(SIGTERM),
-        "HUP" => Ok(SIGHUP),
-        "USR1" => Ok(SIGUSR1),
-        "USR2" => Ok(SIGUSR2),
-        _ => Err(anyhow!("trap: unknown signal {}", s)),
+impl TrapCommand {
+    /// Create a new trap command instance
+    pub fn new() -> Self {
+        TrapCommand
+    }
+
+    /// `trap -p`: print traps as commands that would recreate them
+    fn print_traps(&self, ctx: &ShellContext, filter: &[String]) -> ExecutionResult {
+        let wanted: Vec<TrapCondition> = filter
+            .iter()
+            .filter_map(|s| TrapCondition::parse(s))
+            .collect();
+        let mut out = String::new();
+        if let Ok(traps) = ctx.traps.read() {
+            for (condition, action) in traps.iter() {
+                if !wanted.is_empty() && !wanted.contains(&condition) {
+                    continue;
+                }
+                out.push_str(&format!(
+                    "trap -- {} {}\n",
+                    shell_quote(action),
+                    condition.name()
+                ));
+            }
+        }
+        ExecutionResult::success(0).with_output(out.into_bytes())
     }
 }
 
-fn list_signals() {
-    println!(" 1) SIGHUP    2) SIGINT    3) SIGQUIT   9) SIGKILL  15) SIGTERM");
+impl Default for TrapCommand {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-fn set_handler(sig: i32, cmd: String) -> Result<()> {
-    let mut h = HANDLERS.lock()
-        .map_err(|e| anyhow::anyhow!("Failed to acquire trap handlers lock: {}", e))?;
-    if !h.contains_key(&sig) {
-        // register signal listener thread once per signal
-        // For now, just store the signal handler without actual registration
-        // as signal-hook iterator is not available
-        println!("Would register signal {sig} with handler: {cmd}");
+/// `trap -l`: numbered signal list, five per row like bash
+fn list_signals() -> ExecutionResult {
+    let mut out = String::new();
+    for (i, sig) in ShellSignal::ALL.iter().enumerate() {
+        out.push_str(&format!("{:2}) {sig:<10}", sig.number()));
+        out.push(if i % 5 == 4 { '\n' } else { ' ' });
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
     }
-    h.insert(sig, cmd);
-    Ok(())
+    ExecutionResult::success(0).with_output(out.into_bytes())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn parse_sig() { assert_eq!(parse_signal("INT").unwrap(), SIGINT); }
-} 
 
+    #[test]
+    fn quotes_embedded_single_quotes() {
+        assert_eq!(shell_quote("echo 'bye'"), "'echo '\\''bye'\\'''");
+    }
+}
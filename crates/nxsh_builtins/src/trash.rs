@@ -0,0 +1,320 @@
+//! `trash` builtin - move files to the desktop trash and back
+//!
+//! Syntax:
+//!   trash [put] [-fv] [--] FILE...
+//!   trash list
+//!   trash restore [-v] ITEM...
+//!   trash empty
+//!
+//! `trash FILE...` moves each FILE, directories whole, to the trash the
+//! desktop uses: the freedesktop.org trash on Linux and the BSDs, the
+//! Recycle Bin on Windows and `~/.Trash` on macOS, so the file manager
+//! can put them back. `-f` says nothing about FILEs that do not exist and
+//! `-v` names each FILE trashed. Use `trash put` or `--` for a FILE named
+//! like a subcommand; `rm --trash` does the same as `trash put`.
+//!
+//! `trash list` prints each trashed item, oldest first, with when it was
+//! trashed and where from. `trash restore` puts ITEMs back where they
+//! came from, each ITEM being the name `trash list` shows last in the
+//! trash or the path the file had; when several were trashed from the
+//! same path the newest comes back. It fails rather than overwrite a file
+//! there now. `trash empty` deletes everything in the trash for good.
+//!
+//! In a structured pipeline `trash list` gives a table with the columns
+//! name, path, deleted and type:
+//!
+//!   trash list | where type -eq dir
+//!
+//! The exit status is 0 on success and 1 for an invalid option or when an
+//! item could not be trashed, found or restored.
+
+use crate::common::{hal_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::trash::{empty_trash, list_trash, restore_trashed, trash};
+use nxsh_hal::TrashedItem;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub struct TrashCommand;
+
+impl Builtin for TrashCommand {
+    fn name(&self) -> &'static str {
+        "trash"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Move files to the trash, list, restore or empty it"
+    }
+
+    fn description(&self) -> &'static str {
+        "Move files to the desktop trash instead of deleting them, list what is \
+         there, put items back where they came from, or empty it."
+    }
+
+    fn usage(&self) -> &'static str {
+        "trash [put] [-fv] FILE... | trash list | trash restore [-v] ITEM... | trash empty"
+    }
+
+    fn help(&self) -> &'static str {
+        "Move files to the trash. Use 'trash old.log' to trash a file, 'trash list' \
+         to see what is there and 'trash restore old.log' to get it back."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout.into_bytes())
+            .with_error(outcome.stderr.into_bytes()))
+    }
+}
+
+/// Run trash for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args, &std::env::current_dir()?);
+    io::stdout().write_all(outcome.stdout.as_bytes())?;
+    io::stderr().write_all(outcome.stderr.as_bytes())?;
+    Ok(outcome.status)
+}
+
+/// What `trash list` lists, for the structured `trash`
+pub(crate) fn selected_items(args: &[String]) -> Result<Vec<TrashedItem>, String> {
+    match Subcommand::parse(args)? {
+        Subcommand::List => list_trash().map_err(|e| hal_message(&e)),
+        _ => Err("a structured trash only lists the trash".to_string()),
+    }
+}
+
+/// Move `path`, shown to the user as `shown`, to the trash; shared with
+/// `rm --trash`
+pub(crate) fn put(path: &Path, shown: &str) -> Result<(), String> {
+    trash(path).map_err(|e| format!("cannot move '{shown}' to the trash: {}", hal_message(&e)))
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+impl Outcome {
+    fn failed(message: String) -> Self {
+        Outcome {
+            stdout: String::new(),
+            stderr: format!("trash: {message}\n"),
+            status: 1,
+        }
+    }
+}
+
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+enum Subcommand {
+    Put {
+        force: bool,
+        verbose: bool,
+        files: Vec<String>,
+    },
+    List,
+    Restore {
+        verbose: bool,
+        items: Vec<String>,
+    },
+    Empty,
+}
+
+impl Subcommand {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let (name, rest) = match args.first().map(String::as_str) {
+            Some(name @ ("put" | "list" | "restore" | "empty")) => (name, &args[1..]),
+            _ => ("put", args),
+        };
+        let mut force = false;
+        let mut verbose = false;
+        let mut operands = Vec::new();
+        let mut options_done = false;
+        for arg in rest {
+            if options_done || arg == "-" || !arg.starts_with('-') {
+                operands.push(arg.clone());
+                continue;
+            }
+            match arg.as_str() {
+                "--" => options_done = true,
+                "--force" if name == "put" => force = true,
+                "--verbose" if name == "put" || name == "restore" => verbose = true,
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"))
+                }
+                short => {
+                    for flag in short.chars().skip(1) {
+                        match (flag, name) {
+                            ('f', "put") => force = true,
+                            ('v', "put" | "restore") => verbose = true,
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+            }
+        }
+
+        match name {
+            "put" | "restore" if operands.is_empty() => Err("missing operand".to_string()),
+            "put" => Ok(Subcommand::Put {
+                force,
+                verbose,
+                files: operands,
+            }),
+            "restore" => Ok(Subcommand::Restore {
+                verbose,
+                items: operands,
+            }),
+            _ if !operands.is_empty() => Err(format!("{name}: extra operand '{}'", operands[0])),
+            "list" => Ok(Subcommand::List),
+            _ => Ok(Subcommand::Empty),
+        }
+    }
+}
+
+fn run(args: &[String], cwd: &Path) -> Outcome {
+    let subcommand = match Subcommand::parse(args) {
+        Ok(subcommand) => subcommand,
+        Err(message) => return Outcome::failed(message),
+    };
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    match subcommand {
+        Subcommand::Put {
+            force,
+            verbose,
+            files,
+        } => {
+            for file in &files {
+                let path = cwd.join(file);
+                if force && std::fs::symlink_metadata(&path).is_err() {
+                    continue;
+                }
+                match put(&path, file) {
+                    Ok(()) if verbose => stdout.push_str(&format!("trashed '{file}'\n")),
+                    Ok(()) => {}
+                    Err(message) => stderr.push_str(&format!("trash: {message}\n")),
+                }
+            }
+        }
+        Subcommand::List => match list_trash() {
+            Ok(items) => {
+                for item in items {
+                    let deleted = item.deleted.map_or_else(
+                        || "-".to_string(),
+                        |time| {
+                            chrono::DateTime::<chrono::Local>::from(time)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string()
+                        },
+                    );
+                    let from = item.original_path.as_deref().unwrap_or(&item.path);
+                    stdout.push_str(&format!("{deleted} {}\n", from.display()));
+                }
+            }
+            Err(e) => return Outcome::failed(hal_message(&e)),
+        },
+        Subcommand::Restore { verbose, items } => {
+            let trashed = match list_trash() {
+                Ok(trashed) => trashed,
+                Err(e) => return Outcome::failed(hal_message(&e)),
+            };
+            for wanted in &items {
+                let path = cwd.join(wanted);
+                // The list is oldest first, so the last match is the newest
+                let Some(item) = trashed.iter().rev().find(|item| {
+                    item.id == *wanted || item.original_path.as_deref() == Some(path.as_path())
+                }) else {
+                    stderr.push_str(&format!("trash: {wanted}: not in the trash\n"));
+                    continue;
+                };
+                match restore_trashed(item) {
+                    Ok(to) if verbose => stdout.push_str(&format!("restored '{}'\n", to.display())),
+                    Ok(_) => {}
+                    Err(e) => stderr.push_str(&format!(
+                        "trash: cannot restore '{wanted}': {}\n",
+                        hal_message(&e)
+                    )),
+                }
+            }
+        }
+        Subcommand::Empty => {
+            if let Err(e) = empty_trash() {
+                return Outcome::failed(hal_message(&e));
+            }
+        }
+    }
+    let status = if stderr.is_empty() { 0 } else { 1 };
+    Outcome {
+        stdout,
+        stderr,
+        status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Subcommand, String> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Subcommand::parse(&args)
+    }
+
+    #[test]
+    fn parses_subcommands_and_files() {
+        assert_eq!(
+            parse(&["-v", "a", "b"]),
+            Ok(Subcommand::Put {
+                force: false,
+                verbose: true,
+                files: vec!["a".into(), "b".into()],
+            })
+        );
+        // A file named like a subcommand
+        assert_eq!(
+            parse(&["put", "-f", "--", "list", "-x"]),
+            Ok(Subcommand::Put {
+                force: true,
+                verbose: false,
+                files: vec!["list".into(), "-x".into()],
+            })
+        );
+        assert_eq!(parse(&["list"]), Ok(Subcommand::List));
+        assert_eq!(
+            parse(&["restore", "--verbose", "a"]),
+            Ok(Subcommand::Restore {
+                verbose: true,
+                items: vec!["a".into()],
+            })
+        );
+        assert_eq!(parse(&["empty"]), Ok(Subcommand::Empty));
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        assert_eq!(parse(&[]), Err("missing operand".to_string()));
+        assert_eq!(parse(&["restore"]), Err("missing operand".to_string()));
+        assert_eq!(
+            parse(&["-q", "a"]),
+            Err("invalid option -- 'q'".to_string())
+        );
+        assert_eq!(
+            parse(&["list", "-f"]),
+            Err("invalid option -- 'f'".to_string())
+        );
+        assert_eq!(
+            parse(&["empty", "now"]),
+            Err("empty: extra operand 'now'".to_string())
+        );
+        assert_eq!(
+            parse(&["--all", "a"]),
+            Err("unrecognized option '--all'".to_string())
+        );
+    }
+}
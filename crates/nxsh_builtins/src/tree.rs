@@ -1,55 +1,697 @@
-//! `tree` command  Edirectory tree listing.
-//! Usage: tree [DIR]
-//! Prints ASCII tree up to default depth 3; use -L <n> to limit depth.
-
-use anyhow::Result;
-use walkdir::WalkDir;
-use std::path::Path;
-use tokio::task;
-
-pub async fn tree_cli(args: &[String]) -> Result<()> {
-    let mut depth = 3;
-    let mut path = ".".to_string();
-    let mut idx = 0;
-    if args.get(0).map(|s| s.as_str()) == Some("-L") {
-        depth = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(3);
-        idx = 2;
-    }
-    if let Some(p) = args.get(idx) { path = p.clone(); }
-    let pbuf = Path::new(&path).to_path_buf();
-    task::spawn_blocking(move || display_tree(pbuf, depth)).await??;
-    Ok(())
-}
-
-fn display_tree(root: std::path::PathBuf, depth: usize) -> Result<()> {
-    println!("{}", root.display());
-    for entry in WalkDir::new(&root).min_depth(1).max_depth(depth) {
-        let e = entry?;
-        let depth_level = e.depth();
-        let indent = "━E  ".repeat(depth_level - 1);
-        let name = e.file_name().to_string_lossy();
-        let branch = if e.depth() == depth { "└──" } else { "├──" };
-        println!("{}{} {}", indent, branch, name);
-    }
-    Ok(())
+//! `tree` builtin - list directory contents as a tree
+//!
+//! Syntax:
+//!   tree [-adhnpsC] [-L LEVEL] [-P PATTERN] [-I PATTERN] [--gitignore]
+//!        [--dirsfirst] [--icons] [--noreport] [DIR]...
+//!
+//! Each DIR, or the current directory when none is given, is drawn with its
+//! contents below it, joined by Unicode branches and sorted by name. Hidden
+//! entries are left out unless `-a` is given, `-d` lists directories only
+//! and `-L` stops descending after LEVEL levels. Symbolic links are shown
+//! with their targets but not followed. A count of the directories and
+//! files listed ends the output unless `--noreport` is given.
+//!
+//! `-P` lists only the files whose names match PATTERN and `-I` leaves out
+//! every entry whose name does; PATTERN is a wildcard and may join several
+//! with `|`. `--gitignore` leaves out what the `.gitignore` files in the
+//! listed directories ignore, as well as `.git` itself.
+//!
+//! `-p` and `-s` add permission and size columns, `-h` prints sizes with
+//! units. Names are colored by type when standard output is a terminal,
+//! always with `-C` and never with `-n`, using the theme's `tree.directory`,
+//! `tree.symlink` and `tree.executable` styles or the `ls` colors where it
+//! has none. `--icons` puts the `ls` file icon before each name.
+//!
+//! The exit status is 0 on success and 1 if a DIR could not be listed.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult, TableFormatter};
+use crate::ls::{file_icon, format_permissions};
+use crossterm::style::ContentStyle;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::fmt::Write as _;
+use std::fs::{self, Metadata};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// The `tree` builtin command implementation
+pub struct TreeCommand;
+
+impl Builtin for TreeCommand {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "List directory contents as a tree"
+    }
+
+    fn description(&self) -> &'static str {
+        "Draw directories and their contents as a tree, limited in depth with -L, \
+         filtered by name patterns or .gitignore files, with optional permission \
+         and size columns."
+    }
+
+    fn usage(&self) -> &'static str {
+        "tree [-adhnpsC] [-L LEVEL] [-P PATTERN] [-I PATTERN] [--gitignore] \
+         [--dirsfirst] [--icons] [--noreport] [DIR]..."
+    }
+
+    fn help(&self) -> &'static str {
+        "List directory contents as a tree. Use 'tree -L 2' for two levels or \
+         'tree --gitignore -I target' to skip build output."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let cwd = ctx.cwd.clone();
+        let terminal = ctx.is_interactive() && io::stdout().is_terminal();
+        let outcome = run(args, &cwd, terminal);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run tree for the legacy dispatcher, writing straight to the process's
+/// standard output and error
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let outcome = run(args, &cwd, io::stdout().is_terminal());
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run printed and its exit status
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// `terminal` says whether names are colored when neither `-C` nor `-n`
+/// is given
+fn run(args: &[String], cwd: &Path, terminal: bool) -> Outcome {
+    let mut options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            return Outcome {
+                stdout: Vec::new(),
+                stderr: format!("tree: {message}\n").into_bytes(),
+                status: 1,
+            }
+        }
+    };
+    if options.roots.is_empty() {
+        options.roots.push(".".to_string());
+    }
+
+    let mut tree = Tree {
+        options: &options,
+        colors: options.color.unwrap_or(terminal).then(Colors::load),
+        ignores: Vec::new(),
+        lines: Vec::new(),
+        stderr: String::new(),
+        dirs: 0,
+        files: 0,
+        trouble: false,
+    };
+    for root in &options.roots {
+        tree.root(cwd, root);
+    }
+
+    let mut stdout = tree.render();
+    if options.report {
+        let dirs = count(tree.dirs, "directory", "directories");
+        if options.dirs_only {
+            let _ = writeln!(stdout, "\n{dirs}");
+        } else {
+            let files = count(tree.files, "file", "files");
+            let _ = writeln!(stdout, "\n{dirs}, {files}");
+        }
+    }
+    Outcome {
+        stdout: stdout.into_bytes(),
+        stderr: tree.stderr.into_bytes(),
+        status: i32::from(tree.trouble),
+    }
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{n} {}", if n == 1 { one } else { many })
+}
+
+/// Parsed command line
+#[derive(Debug, Default)]
+struct Options {
+    all: bool,
+    dirs_only: bool,
+    /// `-L`, the deepest level listed
+    level: Option<usize>,
+    /// `-P`, the patterns files must match
+    include: Vec<glob::Pattern>,
+    /// `-I`, the patterns of entries left out
+    exclude: Vec<glob::Pattern>,
+    gitignore: bool,
+    dirs_first: bool,
+    icons: bool,
+    permissions: bool,
+    size: bool,
+    human: bool,
+    /// `-C` or `-n`, or `None` to color on a terminal
+    color: Option<bool>,
+    report: bool,
+    roots: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            report: true,
+            ..Default::default()
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.roots.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--gitignore" => options.gitignore = true,
+                "--dirsfirst" => options.dirs_first = true,
+                "--icons" => options.icons = true,
+                "--noreport" => options.report = false,
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'a' => options.all = true,
+                            'd' => options.dirs_only = true,
+                            'p' => options.permissions = true,
+                            's' => options.size = true,
+                            'h' => {
+                                options.size = true;
+                                options.human = true;
+                            }
+                            'C' => options.color = Some(true),
+                            'n' => options.color = Some(false),
+                            'L' | 'P' | 'I' => {
+                                let attached = &short[2 + at..];
+                                let value = if attached.is_empty() {
+                                    iter.next().cloned().ok_or_else(|| {
+                                        format!("option requires an argument -- '{flag}'")
+                                    })?
+                                } else {
+                                    attached.to_string()
+                                };
+                                options.value(flag, &value)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
+                        }
+                    }
+                }
+                _ => options.roots.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn value(&mut self, flag: char, value: &str) -> Result<(), String> {
+        match flag {
+            'L' => match value.parse() {
+                Ok(level) if level > 0 => self.level = Some(level),
+                _ => return Err(format!("invalid level '{value}'")),
+            },
+            'P' => self.include.extend(patterns(value)?),
+            _ => self.exclude.extend(patterns(value)?),
+        }
+        Ok(())
+    }
+}
+
+/// The wildcards of a `-P` or `-I` PATTERN, which joins them with `|`
+fn patterns(value: &str) -> Result<Vec<glob::Pattern>, String> {
+    value
+        .split('|')
+        .map(|part| glob::Pattern::new(part).map_err(|e| format!("invalid pattern '{part}': {e}")))
+        .collect()
+}
+
+/// How one kind of name is painted
+enum Paint {
+    /// An SGR sequence, such as the `ls` defaults
+    Sgr(&'static str),
+    /// A theme style
+    Style(ContentStyle),
+}
+
+impl Paint {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Paint::Sgr(sgr) => format!("\x1b[{sgr}m{text}\x1b[m"),
+            Paint::Style(style) => style.apply(text).to_string(),
+        }
+    }
+}
+
+/// The colors of names by type
+struct Colors {
+    directory: Paint,
+    symlink: Paint,
+    executable: Paint,
+}
+
+impl Colors {
+    /// The active theme's colors, or those of `ls` where it has none
+    fn load() -> Self {
+        let mut colors = Colors {
+            directory: Paint::Sgr("01;34"),
+            symlink: Paint::Sgr("01;36"),
+            executable: Paint::Sgr("01;32"),
+        };
+        let theme_name = nxsh_ui::UiConfig::default().theme_name;
+        if let Ok(theme) = nxsh_ui::get_theme(&theme_name) {
+            let style = |name: &str| {
+                theme
+                    .styles
+                    .get(name)
+                    .map(|style| Paint::Style(style.clone().into()))
+            };
+            if let Some(paint) = style("tree.directory") {
+                colors.directory = paint;
+            }
+            if let Some(paint) = style("tree.symlink") {
+                colors.symlink = paint;
+            }
+            if let Some(paint) = style("tree.executable") {
+                colors.executable = paint;
+            }
+        }
+        colors
+    }
+}
+
+/// The rules of one `.gitignore` file
+struct Ignore {
+    /// The directory holding the file, which patterns with a `/` are
+    /// relative to
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    fn load(dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        Some(Ignore {
+            base: dir.to_path_buf(),
+            rules: text.lines().filter_map(Rule::parse).collect(),
+        })
+    }
+}
+
+/// One line of a `.gitignore` file
+#[derive(Debug)]
+struct Rule {
+    pattern: glob::Pattern,
+    /// `!`, which brings back what an earlier rule ignored
+    negated: bool,
+    /// A trailing `/`, which matches directories only
+    dir_only: bool,
+    /// A `/` before the end, which matches the path below the file's
+    /// directory instead of the name at any depth
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // `\#` and `\!` start patterns with those characters
+        let line = line.strip_prefix('\\').unwrap_or(line);
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        Some(Rule {
+            pattern: glob::Pattern::new(line).ok()?,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+}
+
+/// One entry of a directory that is listed
+struct Entry {
+    name: String,
+    path: PathBuf,
+    /// The entry's own metadata, not that of a symlink's target
+    metadata: Metadata,
+    /// A directory or a symlink to one
+    is_dir: bool,
+}
+
+/// One line of the listing
+struct Line {
+    /// The branches drawn before the name
+    indent: String,
+    /// The `-p` and `-s` columns, empty for the roots
+    cells: Vec<String>,
+    name: String,
+}
+
+/// The state of one tree run
+struct Tree<'a> {
+    options: &'a Options,
+    colors: Option<Colors>,
+    /// The `.gitignore` rules of the directories being walked, innermost last
+    ignores: Vec<Ignore>,
+    lines: Vec<Line>,
+    stderr: String,
+    dirs: usize,
+    files: usize,
+    trouble: bool,
+}
+
+impl Tree<'_> {
+    fn root(&mut self, cwd: &Path, root: &str) {
+        let path = cwd.join(root);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => {
+                let _ = writeln!(self.stderr, "tree: {root}: Not a directory");
+                self.trouble = true;
+                return;
+            }
+            Err(e) => {
+                let _ = writeln!(self.stderr, "tree: {root}: {}", io_message(&e));
+                self.trouble = true;
+                return;
+            }
+        }
+        let name = match &self.colors {
+            Some(colors) => colors.directory.apply(root),
+            None => root.to_string(),
+        };
+        self.lines.push(Line {
+            indent: String::new(),
+            cells: Vec::new(),
+            name,
+        });
+        self.walk(&path, "", 1);
+    }
+
+    /// List the contents of DIR, at DEPTH below the root, under the line
+    /// just added for it
+    fn walk(&mut self, dir: &Path, indent: &str, depth: usize) {
+        let ignore = if self.options.gitignore {
+            Ignore::load(dir)
+        } else {
+            None
+        };
+        let pushed = ignore.is_some();
+        self.ignores.extend(ignore);
+
+        match self.entries(dir) {
+            Ok(entries) => self.branches(entries, indent, depth),
+            Err(_) => {
+                if let Some(line) = self.lines.last_mut() {
+                    line.name.push_str("  [error opening dir]");
+                }
+                self.trouble = true;
+            }
+        }
+
+        if pushed {
+            self.ignores.pop();
+        }
+    }
+
+    fn branches(&mut self, entries: Vec<Entry>, indent: &str, depth: usize) {
+        let count = entries.len();
+        for (i, entry) in entries.into_iter().enumerate() {
+            let last = i + 1 == count;
+            if entry.is_dir {
+                self.dirs += 1;
+            } else {
+                self.files += 1;
+            }
+            self.lines.push(Line {
+                indent: format!("{indent}{}", if last { "└── " } else { "├── " }),
+                cells: self.cells(&entry),
+                name: self.label(&entry),
+            });
+            let deeper = !matches!(self.options.level, Some(level) if depth >= level);
+            if entry.metadata.is_dir() && deeper {
+                let indent = format!("{indent}{}", if last { "    " } else { "│   " });
+                self.walk(&entry.path, &indent, depth + 1);
+            }
+        }
+    }
+
+    /// The entries of DIR that are listed, in order
+    fn entries(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let options = self.options;
+        let mut entries = Vec::new();
+        for dirent in fs::read_dir(dir)? {
+            let dirent = dirent?;
+            let name = dirent.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') && !options.all {
+                continue;
+            }
+            let path = dirent.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            let is_dir = metadata.is_dir()
+                || (metadata.file_type().is_symlink()
+                    && fs::metadata(&path).is_ok_and(|target| target.is_dir()));
+            if options.dirs_only && !is_dir {
+                continue;
+            }
+            if options.exclude.iter().any(|pattern| pattern.matches(&name)) {
+                continue;
+            }
+            if !is_dir
+                && !options.include.is_empty()
+                && !options.include.iter().any(|pattern| pattern.matches(&name))
+            {
+                continue;
+            }
+            if options.gitignore && (name == ".git" || self.ignored(&path, metadata.is_dir())) {
+                continue;
+            }
+            entries.push(Entry {
+                name,
+                path,
+                metadata,
+                is_dir,
+            });
+        }
+        entries.sort_by(|a, b| {
+            let a_later = options.dirs_first && !a.is_dir;
+            let b_later = options.dirs_first && !b.is_dir;
+            a_later.cmp(&b_later).then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(entries)
+    }
+
+    /// Whether PATH is ignored, going by the last matching rule of the
+    /// innermost `.gitignore` that has one
+    fn ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        for ignore in self.ignores.iter().rev() {
+            let Ok(relative) = path.strip_prefix(&ignore.base) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            for rule in ignore.rules.iter().rev() {
+                if rule.dir_only && !is_dir {
+                    continue;
+                }
+                let subject = if rule.anchored {
+                    relative.as_str()
+                } else {
+                    &name
+                };
+                if rule.pattern.matches_with(subject, options) {
+                    return !rule.negated;
+                }
+            }
+        }
+        false
+    }
+
+    fn cells(&self, entry: &Entry) -> Vec<String> {
+        let mut cells = Vec::new();
+        if self.options.permissions {
+            cells.push(format_permissions(&entry.metadata));
+        }
+        if self.options.size {
+            let len = entry.metadata.len();
+            cells.push(if self.options.human {
+                TableFormatter::new().format_size(len)
+            } else {
+                len.to_string()
+            });
+        }
+        cells
+    }
+
+    /// The name of ENTRY as listed, with its icon, color and link target
+    fn label(&self, entry: &Entry) -> String {
+        let file_type = entry.metadata.file_type();
+        let paint = self.colors.as_ref().and_then(|colors| {
+            if file_type.is_symlink() {
+                Some(&colors.symlink)
+            } else if file_type.is_dir() {
+                Some(&colors.directory)
+            } else if executable(&entry.metadata) {
+                Some(&colors.executable)
+            } else {
+                None
+            }
+        });
+
+        let mut label = String::new();
+        if self.options.icons {
+            label.push_str(file_icon(&entry.path, &entry.metadata));
+            label.push(' ');
+        }
+        match paint {
+            Some(paint) => label.push_str(&paint.apply(&entry.name)),
+            None => label.push_str(&entry.name),
+        }
+        if file_type.is_symlink() {
+            if let Ok(target) = fs::read_link(&entry.path) {
+                let _ = write!(label, " -> {}", target.display());
+            }
+        }
+        label
+    }
+
+    /// The listing, with the columns of every entry lined up by the table
+    /// formatter and sizes aligned to the right
+    fn render(&self) -> String {
+        let size_width = if self.options.size {
+            let sizes = self.lines.iter().filter_map(|line| line.cells.last());
+            sizes.map(String::len).max().unwrap_or(0)
+        } else {
+            0
+        };
+        let mut table = TableFormatter::new();
+        for line in self.lines.iter().filter(|line| !line.cells.is_empty()) {
+            let mut cells = line.cells.clone();
+            if self.options.size {
+                if let Some(size) = cells.last_mut() {
+                    *size = format!("{size:>size_width$}");
+                }
+            }
+            table.add_row(cells);
+        }
+        let columns = table.format();
+        let mut columns = columns.lines();
+
+        let mut out = String::new();
+        for line in &self.lines {
+            out.push_str(&line.indent);
+            if !line.cells.is_empty() {
+                let _ = write!(out, "[{}]  ", columns.next().unwrap_or_default());
+            }
+            out.push_str(&line.name);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(unix)]
+fn executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn executable(_metadata: &Metadata) -> bool {
+    false
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
-    use std::fs;
-    #[tokio::test]
-    async fn tree_runs() {
-        let d = tempdir().unwrap();
-        fs::create_dir(d.path().join("sub")).unwrap();
-        tree_cli(&[d.path().to_string_lossy().into()]).await.unwrap();
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
     }
-} 
 
+    #[test]
+    fn parses_clustered_and_attached_values() {
+        let parsed = options(&["-adL2", "-I", "target|*.log", "--noreport", "src"]).unwrap();
+        assert!(parsed.all && parsed.dirs_only && !parsed.report);
+        assert_eq!(parsed.level, Some(2));
+        assert_eq!(parsed.exclude.len(), 2);
+        assert_eq!(parsed.roots, ["src"]);
 
-/// Execute function stub
-pub fn execute(_args: &[String], _context: &crate::common::BuiltinContext) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+        let human = options(&["-h"]).unwrap();
+        assert!(human.size && human.human);
+
+        assert_eq!(options(&["-L", "0"]).unwrap_err(), "invalid level '0'");
+        assert_eq!(
+            options(&["-P"]).unwrap_err(),
+            "option requires an argument -- 'P'"
+        );
+    }
+
+    #[test]
+    fn gitignore_rules_follow_git() {
+        let rule = Rule::parse("!/build/").unwrap();
+        assert!(rule.negated && rule.dir_only && rule.anchored);
+        assert_eq!(rule.pattern.as_str(), "build");
+        assert!(Rule::parse("# comment").is_none());
+        assert!(Rule::parse("   ").is_none());
+
+        let options = Options::default();
+        let base = PathBuf::from("/repo");
+        let tree = Tree {
+            options: &options,
+            colors: None,
+            ignores: vec![Ignore {
+                base: base.clone(),
+                rules: ["*.log", "!keep.log", "target/", "docs/*.tmp"]
+                    .into_iter()
+                    .filter_map(Rule::parse)
+                    .collect(),
+            }],
+            lines: Vec::new(),
+            stderr: String::new(),
+            dirs: 0,
+            files: 0,
+            trouble: false,
+        };
+        assert!(tree.ignored(&base.join("src/debug.log"), false));
+        assert!(!tree.ignored(&base.join("keep.log"), false));
+        assert!(tree.ignored(&base.join("target"), true));
+        assert!(!tree.ignored(&base.join("target"), false));
+        assert!(tree.ignored(&base.join("docs/a.tmp"), false));
+        assert!(!tree.ignored(&base.join("docs/sub/a.tmp"), false));
+    }
 }
@@ -1,72 +1,420 @@
-//! `ulimit` builtin  Eview or set resource limits.
-//! Supported options (subset):
-//!   -a  : list all limits
-//!   -n N: set/open files soft limit
-//!   -c N: set core file size soft limit
-//! When setting, `N` may be `unlimited`.
-//! On Windows this builtin only prints an unsupported message.
-
-use anyhow::Result;
-
-#[cfg(unix)]
-use nix::sys::resource::{getrlimit, setrlimit, Resource};
-
-pub fn ulimit_cli(_args: &[String]) -> Result<()> {
-    #[cfg(windows)]
-    {
-        println!("ulimit: not supported on Windows");
-        Ok(())
-    }
-    #[cfg(unix)]
-    {
-        if args.is_empty() || (args.len() == 1 && args[0] == "-a") {
-            print_limit("core file size", Resource::RLIMIT_CORE)?;
-            print_limit("open files", Resource::RLIMIT_NOFILE)?;
-            return Ok(());
+//! `ulimit` builtin - report and set resource limits
+//!
+//! Syntax:
+//!   ulimit [-SH] [-a | -cdefilmnqrstuvx [LIMIT]]...
+//!
+//! Each resource option prints the limit on that resource, or sets it when
+//! followed by LIMIT, which is a number in the unit shown by `-a`,
+//! `unlimited`, or `soft` or `hard` for the current soft or hard limit.
+//! Without an option the file size limit (`-f`) is used.
+//!
+//!   -S  use the soft limit, which is in force and may be raised up to the
+//!       hard limit
+//!   -H  use the hard limit, which only a privileged user may raise
+//!   -a  print every limit, labelled with its unit and option
+//!
+//! New limits apply to the shell and every command it runs afterwards.
+//! Setting a limit without `-S` or `-H` sets both; printing one shows the
+//! soft limit unless `-H` is given. On Windows only `-t`, `-u` and `-v`
+//! are available, enforced on each foreground job through its Job Object.
+//!
+//! The exit status is 0 on success, 1 when a limit cannot be read or set,
+//! and 2 on usage errors.
+
+use crate::common::{hal_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::resource::{self, Resource, ResourceLimit};
+use nxsh_hal::HalError;
+use std::io::{self, Write};
+
+const USAGE: &str = "ulimit [-SH] [-a | -cdefilmnqrstuvx [LIMIT]]";
+
+/// The `ulimit` builtin command implementation
+pub struct UlimitCommand;
+
+impl Builtin for UlimitCommand {
+    fn name(&self) -> &'static str {
+        "ulimit"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Report and set resource limits"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print or change the soft and hard limits on resources such as open files, \
+         core size and CPU time for the shell and the commands it runs."
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Limit resources. Use 'ulimit -a' to see every limit, 'ulimit -n 4096' to allow \
+         more open files or 'ulimit -c unlimited' to keep core dumps."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run ulimit for the legacy dispatcher
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+#[derive(Default)]
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+/// How a resource is shown and which option selects it
+struct Spec {
+    option: char,
+    resource: Resource,
+    description: &'static str,
+    unit: Option<&'static str>,
+    /// Resource units in one displayed unit
+    factor: u64,
+}
+
+static SPECS: [Spec; 15] = [
+    Spec {
+        option: 'c',
+        resource: Resource::CoreSize,
+        description: "core file size",
+        unit: Some("blocks"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'd',
+        resource: Resource::DataSize,
+        description: "data seg size",
+        unit: Some("kbytes"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'e',
+        resource: Resource::Nice,
+        description: "scheduling priority",
+        unit: None,
+        factor: 1,
+    },
+    Spec {
+        option: 'f',
+        resource: Resource::FileSize,
+        description: "file size",
+        unit: Some("blocks"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'i',
+        resource: Resource::PendingSignals,
+        description: "pending signals",
+        unit: None,
+        factor: 1,
+    },
+    Spec {
+        option: 'l',
+        resource: Resource::LockedMemory,
+        description: "max locked memory",
+        unit: Some("kbytes"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'm',
+        resource: Resource::ResidentSet,
+        description: "max memory size",
+        unit: Some("kbytes"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'n',
+        resource: Resource::OpenFiles,
+        description: "open files",
+        unit: None,
+        factor: 1,
+    },
+    Spec {
+        option: 'q',
+        resource: Resource::MessageQueue,
+        description: "POSIX message queues",
+        unit: Some("bytes"),
+        factor: 1,
+    },
+    Spec {
+        option: 'r',
+        resource: Resource::RealtimePriority,
+        description: "real-time priority",
+        unit: None,
+        factor: 1,
+    },
+    Spec {
+        option: 's',
+        resource: Resource::StackSize,
+        description: "stack size",
+        unit: Some("kbytes"),
+        factor: 1024,
+    },
+    Spec {
+        option: 't',
+        resource: Resource::CpuTime,
+        description: "cpu time",
+        unit: Some("seconds"),
+        factor: 1,
+    },
+    Spec {
+        option: 'u',
+        resource: Resource::Processes,
+        description: "max user processes",
+        unit: None,
+        factor: 1,
+    },
+    Spec {
+        option: 'v',
+        resource: Resource::VirtualMemory,
+        description: "virtual memory",
+        unit: Some("kbytes"),
+        factor: 1024,
+    },
+    Spec {
+        option: 'x',
+        resource: Resource::FileLocks,
+        description: "file locks",
+        unit: None,
+        factor: 1,
+    },
+];
+
+fn spec(option: char) -> Option<&'static Spec> {
+    SPECS.iter().find(|spec| spec.option == option)
+}
+
+impl Spec {
+    /// The `-a` label, with the unit and option lined up at column 40
+    fn label(&self) -> String {
+        let unit = match self.unit {
+            Some(unit) => format!("({unit}, -{})", self.option),
+            None => format!("(-{})", self.option),
+        };
+        let pad = 40usize
+            .saturating_sub(self.description.len() + unit.len())
+            .max(1);
+        format!("{}{:pad$}{unit} ", self.description, "")
+    }
+
+    fn show(&self, limit: Option<u64>) -> String {
+        match limit {
+            Some(value) => (value / self.factor).to_string(),
+            None => "unlimited".to_string(),
         }
-        if args.len() == 2 {
-            let resource = match args[0].as_str() {
-                "-n" => Resource::RLIMIT_NOFILE,
-                "-c" => Resource::RLIMIT_CORE,
-                _ => return Err(anyhow!("ulimit: unsupported option")),
-            };
-            let val = if args[1] == "unlimited" { 
-                u64::MAX 
-            } else { 
-                args[1].parse::<u64>()? 
+    }
+}
+
+struct Invocation {
+    soft: bool,
+    hard: bool,
+    all: bool,
+    /// The resources named, each with the LIMIT that followed it
+    requests: Vec<(&'static Spec, Option<String>)>,
+}
+
+impl Invocation {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut invocation = Invocation {
+            soft: false,
+            hard: false,
+            all: false,
+            requests: Vec::new(),
+        };
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                continue;
+            }
+            let Some(options) = arg.strip_prefix('-').filter(|o| !o.is_empty()) else {
+                // A LIMIT with no option before it sets the file size
+                if !invocation.requests.is_empty() {
+                    return Err(format!("{arg}: too many arguments"));
+                }
+                invocation
+                    .requests
+                    .push((spec('f').unwrap(), Some(arg.clone())));
+                continue;
             };
-            match setrlimit(resource, val, val) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(anyhow!("Failed to set limit: {}", e)),
+            for option in options.chars() {
+                match option {
+                    'S' => invocation.soft = true,
+                    'H' => invocation.hard = true,
+                    'a' => invocation.all = true,
+                    _ => match spec(option) {
+                        Some(spec) => invocation.requests.push((spec, None)),
+                        None => return Err(format!("-{option}: invalid option")),
+                    },
+                }
+            }
+            let takes_limit = options.chars().last().and_then(spec).is_some();
+            if takes_limit {
+                if let Some(limit) = args.next_if(|next| !next.starts_with('-')) {
+                    if let Some(last) = invocation.requests.last_mut() {
+                        last.1 = Some(limit.clone());
+                    }
+                }
             }
+        }
+        if invocation.requests.is_empty() && !invocation.all {
+            invocation.requests.push((spec('f').unwrap(), None));
+        }
+        Ok(invocation)
+    }
+
+    fn run(self) -> Outcome {
+        let mut outcome = Outcome::default();
+        if self.all {
+            for spec in SPECS.iter().filter(|spec| spec.resource.is_supported()) {
+                self.print(spec, true, &mut outcome);
+            }
+            return outcome;
+        }
+        let labelled = self.requests.len() > 1;
+        for (spec, limit) in &self.requests {
+            match limit {
+                Some(limit) => self.set(spec, limit, &mut outcome),
+                None => self.print(spec, labelled, &mut outcome),
+            }
+        }
+        outcome
+    }
+
+    fn print(&self, spec: &Spec, labelled: bool, outcome: &mut Outcome) {
+        let limit = match resource::get_limit(spec.resource) {
+            Ok(limit) => limit,
+            Err(e) => return fail(outcome, spec, "cannot get limit", &e),
+        };
+        let value = if self.hard && !self.soft {
+            limit.hard
+        } else {
+            limit.soft
+        };
+        let label = if labelled {
+            spec.label()
         } else {
-            Err(anyhow!("ulimit: invalid usage"))
+            String::new()
+        };
+        outcome
+            .stdout
+            .extend(format!("{label}{}\n", spec.show(value)).into_bytes());
+    }
+
+    fn set(&self, spec: &Spec, limit: &str, outcome: &mut Outcome) {
+        let current = match resource::get_limit(spec.resource) {
+            Ok(current) => current,
+            Err(e) => return fail(outcome, spec, "cannot get limit", &e),
+        };
+        let value = match limit {
+            "unlimited" => None,
+            "soft" => current.soft,
+            "hard" => current.hard,
+            _ => match limit
+                .parse::<u64>()
+                .ok()
+                .and_then(|n| n.checked_mul(spec.factor))
+            {
+                Some(value) => Some(value),
+                None => {
+                    outcome
+                        .stderr
+                        .extend(format!("ulimit: {limit}: invalid number\n").into_bytes());
+                    outcome.status = 1;
+                    return;
+                }
+            },
+        };
+        let both = self.soft == self.hard;
+        let new = ResourceLimit {
+            soft: if self.soft || both {
+                value
+            } else {
+                current.soft
+            },
+            hard: if self.hard || both {
+                value
+            } else {
+                current.hard
+            },
+        };
+        if let Err(e) = resource::set_limit(spec.resource, new) {
+            fail(outcome, spec, "cannot modify limit", &e);
         }
     }
 }
 
-#[cfg(unix)]
-fn print_limit(name: &str, resource: Resource) -> Result<()> {
-    match getrlimit(resource) {
-        Ok((soft, _hard)) => {
-            let v = if soft == u64::MAX { 
-                "unlimited".to_string() 
-            } else { 
-                soft.to_string() 
-            };
-            println!("{}: {}", name, v);
-            Ok(())
-        }
-        Err(e) => Err(anyhow!("Failed to get {}: {}", name, e))
+fn fail(outcome: &mut Outcome, spec: &Spec, what: &str, error: &HalError) {
+    let reason = match error {
+        HalError::Unsupported(_) => "not supported on this platform".to_string(),
+        other => hal_message(other),
+    };
+    outcome
+        .stderr
+        .extend(format!("ulimit: {}: {what}: {reason}\n", spec.description).into_bytes());
+    outcome.status = 1;
+}
+
+fn run(args: &[String]) -> Outcome {
+    match Invocation::parse(args) {
+        Ok(invocation) => invocation.run(),
+        Err(message) => Outcome {
+            stdout: Vec::new(),
+            stderr: format!("ulimit: {message}\nulimit: usage: {USAGE}\n").into_bytes(),
+            status: 2,
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
     #[test]
-    fn list_limits() {
-        let _ = ulimit_cli(&["-a".into()]);
+    fn labels_line_up_like_bash() {
+        assert_eq!(
+            spec('c').unwrap().label(),
+            "core file size              (blocks, -c) "
+        );
+        assert_eq!(
+            spec('n').unwrap().label(),
+            "open files                          (-n) "
+        );
     }
-} 
 
+    #[test]
+    fn limits_attach_to_the_last_option() {
+        let invocation = Invocation::parse(&args(&["-S", "-cn", "64", "-t"])).unwrap();
+        assert!(invocation.soft && !invocation.hard);
+        let requests: Vec<_> = invocation
+            .requests
+            .iter()
+            .map(|(spec, limit)| (spec.option, limit.as_deref()))
+            .collect();
+        assert_eq!(requests, [('c', None), ('n', Some("64")), ('t', None)]);
+
+        let invocation = Invocation::parse(&args(&["unlimited"])).unwrap();
+        assert_eq!(invocation.requests[0].0.option, 'f');
+        assert!(Invocation::parse(&args(&["-k"])).is_err());
+    }
+}
@@ -1,78 +1,277 @@
-//! `umask` builtin  Eget or set the file creation mask.
-//! Usage:
-//!   umask           # print current mask in 4-digit octal (e.g., 0022)
-//!   umask MODE      # set mask to MODE (octal)
-//!   umask -S        # symbolic output (rwx style)
+//! `umask` builtin - report and set the file creation mask
 //!
-//! On Unix uses `libc::umask`. On Windows prints unsupported message.
+//! Syntax:
+//!   umask [-p] [-S] [MODE]
+//!
+//! Without MODE the mask is printed as four octal digits. MODE is either an
+//! octal number or a symbolic mode like `chmod` takes, such as `u=rwx,g=rx,o=`
+//! or `go-w`, which names the permissions new files may have rather than
+//! the ones they lose. The mask applies to the shell and every command it
+//! runs afterwards.
+//!
+//!   -S  print the mask in symbolic form
+//!   -p  print it as a `umask` command that restores it
+//!
+//! The exit status is 0 on success, 1 for an invalid MODE and 2 on usage
+//! errors.
+
+use crate::common::{BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::resource;
+use std::io::{self, Write};
+
+const USAGE: &str = "umask [-p] [-S] [MODE]";
+
+/// The `umask` builtin command implementation
+pub struct UmaskCommand;
+
+impl Builtin for UmaskCommand {
+    fn name(&self) -> &'static str {
+        "umask"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Report and set the file creation mask"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the mask of permission bits removed from newly created files, or set it \
+         from an octal number or a symbolic mode."
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Control default file permissions. Use 'umask 077' to keep new files private or \
+         'umask -S' to see which permissions new files get."
+    }
+
+    fn execute(&self, _ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = run(args);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run umask for the legacy dispatcher
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = run(args);
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
 
-use anyhow::Result;
+impl Outcome {
+    fn error(message: String, status: i32) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("umask: {message}\n").into_bytes(),
+            status,
+        }
+    }
+}
 
-#[cfg(unix)]
-use nix::sys::stat::{umask, Mode};
+fn run(args: &[String]) -> Outcome {
+    let mut symbolic = false;
+    let mut reusable = false;
+    let mut rest = args;
+    while let Some(arg) = rest.first() {
+        if arg == "--" {
+            rest = &rest[1..];
+            break;
+        }
+        let Some(options) = arg.strip_prefix('-').filter(|o| !o.is_empty()) else {
+            break;
+        };
+        for option in options.chars() {
+            match option {
+                'S' => symbolic = true,
+                'p' => reusable = true,
+                _ => {
+                    return Outcome::error(
+                        format!("-{option}: invalid option\numask: usage: {USAGE}"),
+                        2,
+                    )
+                }
+            }
+        }
+        rest = &rest[1..];
+    }
 
-pub fn umask_cli(args: &[String]) -> Result<()> {
-    #[cfg(windows)]
-    {
-        if args.is_empty() {
-            // Windows: no real umask; print 0000 for compatibility
-            println!("0000");
-            return Ok(());
+    let mask = match rest {
+        [] => resource::umask(),
+        [mode] => {
+            let result = if mode.starts_with(|c: char| c.is_ascii_digit()) {
+                parse_octal(mode)
+            } else {
+                apply_symbolic(mode, resource::umask())
+            };
+            match result {
+                Ok(mask) => {
+                    resource::set_umask(mask);
+                    // A new mask is only echoed when asked for in symbolic form
+                    if !symbolic {
+                        return Outcome {
+                            stdout: Vec::new(),
+                            stderr: Vec::new(),
+                            status: 0,
+                        };
+                    }
+                    mask
+                }
+                Err(message) => return Outcome::error(message, 1),
+            }
         }
-        if args[0] == "-S" {
-            // All permissions allowed
-            println!("u=rwx,g=rwx,o=rwx");
-            return Ok(());
+        [_, extra, ..] => {
+            return Outcome::error(
+                format!("{extra}: too many arguments\numask: usage: {USAGE}"),
+                2,
+            )
         }
-        // Accept but ignore setting
-        println!("umask: setting ignored on Windows");
-    Ok(())
+    };
+
+    let shown = if symbolic {
+        symbolic_form(mask)
+    } else {
+        format!("{mask:04o}")
+    };
+    let line = match (reusable, symbolic) {
+        (true, true) => format!("umask -S {shown}\n"),
+        (true, false) => format!("umask {shown}\n"),
+        _ => format!("{shown}\n"),
+    };
+    Outcome {
+        stdout: line.into_bytes(),
+        stderr: Vec::new(),
+        status: 0,
+    }
+}
+
+fn parse_octal(mode: &str) -> Result<u32, String> {
+    if !mode.chars().all(|c| ('0'..='7').contains(&c)) {
+        return Err(format!("{mode}: octal number out of range"));
     }
-    #[cfg(unix)]
-    {
-        if args.is_empty() {
-            // Get current umask by setting to 0 and restoring
-            let current = umask(Mode::empty());
-            umask(current); // restore
-            println!("{:04o}", current.bits());
-            return Ok(());
+    match u32::from_str_radix(mode, 8) {
+        Ok(mask) if mask <= 0o777 => Ok(mask),
+        _ => Err(format!("{mode}: octal number out of range")),
+    }
+}
+
+/// The permissions a mask leaves, like `u=rwx,g=rx,o=rx`
+fn symbolic_form(mask: u32) -> String {
+    let allowed = !mask & 0o777;
+    [('u', 6), ('g', 3), ('o', 0)]
+        .iter()
+        .map(|&(who, shift)| {
+            let bits = (allowed >> shift) & 0o7;
+            let perms: String = [('r', 0o4), ('w', 0o2), ('x', 0o1)]
+                .iter()
+                .filter(|&&(_, bit)| bits & bit != 0)
+                .map(|&(c, _)| c)
+                .collect();
+            format!("{who}={perms}")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Apply a symbolic MODE to the permissions `mask` allows, returning the
+/// new mask
+fn apply_symbolic(mode: &str, mask: u32) -> Result<u32, String> {
+    let mut allowed = !mask & 0o777;
+    for clause in mode.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(bits) = chars.peek().and_then(|&c| class(c)) {
+            who |= bits;
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o777;
         }
-        if args[0] == "-S" {
-            // Print symbolic permissions allowed (complement of mask)
-            let current = umask(Mode::empty());
-            umask(current);
-            let sym = symbolic_from_mask(current.bits());
-            println!("{}", sym);
-            return Ok(());
+        if chars.peek().is_none() {
+            return Err(format!("{mode}: invalid symbolic mode operator"));
+        }
+        while let Some(op) = chars.next() {
+            if !matches!(op, '+' | '-' | '=') {
+                return Err(format!("`{op}': invalid symbolic mode operator"));
+            }
+            let mut perms = 0;
+            while let Some(&c) = chars.peek() {
+                if matches!(c, '+' | '-' | '=') {
+                    break;
+                }
+                perms |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    // Copy another class's permissions to the ones named
+                    'u' => ((allowed >> 6) & 0o7) * 0o111,
+                    'g' => ((allowed >> 3) & 0o7) * 0o111,
+                    'o' => (allowed & 0o7) * 0o111,
+                    _ => return Err(format!("`{c}': invalid symbolic mode character")),
+                };
+                chars.next();
+            }
+            let perms = perms & who;
+            match op {
+                '+' => allowed |= perms,
+                '-' => allowed &= !perms,
+                _ => allowed = (allowed & !who) | perms,
+            }
         }
-        let new_mask = u32::from_str_radix(&args[0], 8).map_err(|_| anyhow!("umask: invalid mode"))?;
-        let _prev = umask(Mode::from_bits_truncate(new_mask));
-        Ok(())
     }
+    Ok(!allowed & 0o777)
 }
 
-#[cfg(unix)]
-fn symbolic_from_mask(mask_bits: u32) -> String {
-    // Allowed permissions = 0777 & !mask
-    let allowed = 0o777 & (!mask_bits);
-    let u = (allowed >> 6) & 0o7;
-    let g = (allowed >> 3) & 0o7;
-    let o = allowed & 0o7;
-    fn to_rwx(bits: u32) -> String {
-        let r = if bits & 0b100 != 0 { 'r' } else { '-' };
-        let w = if bits & 0b010 != 0 { 'w' } else { '-' };
-        let x = if bits & 0b001 != 0 { 'x' } else { '-' };
-        format!("{}{}{}", r,w,x)
+fn class(c: char) -> Option<u32> {
+    match c {
+        'u' => Some(0o700),
+        'g' => Some(0o070),
+        'o' => Some(0o007),
+        'a' => Some(0o777),
+        _ => None,
     }
-    format!("u={},g={},o={}", to_rwx(u), to_rwx(g), to_rwx(o))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
     #[test]
-    fn get_umask() {
-        let _ = umask_cli(&[]);
+    fn symbolic_modes_name_what_is_allowed() {
+        assert_eq!(apply_symbolic("u=rwx,g=rx,o=", 0o000), Ok(0o027));
+        assert_eq!(apply_symbolic("go-w", 0o000), Ok(0o022));
+        assert_eq!(apply_symbolic("a+r,o-x", 0o077), Ok(0o033));
+        assert_eq!(apply_symbolic("g=u", 0o077), Ok(0o007));
+        assert_eq!(symbolic_form(0o022), "u=rwx,g=rx,o=rx");
+        assert_eq!(symbolic_form(0o077), "u=rwx,g=,o=");
     }
-} 
 
+    #[test]
+    fn rejects_bad_modes() {
+        assert_eq!(
+            apply_symbolic("u*r", 0o022),
+            Err("`*': invalid symbolic mode operator".to_string())
+        );
+        assert_eq!(
+            apply_symbolic("u=rq", 0o022),
+            Err("`q': invalid symbolic mode character".to_string())
+        );
+        assert_eq!(
+            parse_octal("1777"),
+            Err("1777: octal number out of range".to_string())
+        );
+        assert_eq!(parse_octal("0027"), Ok(0o027));
+    }
+}
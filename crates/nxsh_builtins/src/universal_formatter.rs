@@ -82,4 +82,60 @@ impl UniversalFormatter {
     pub fn format_file_listing(&self, _files: &[FileInfo]) -> Result<String, String> {
         Ok(String::new())
     }
+
+    /// Draw `rows` under `headers` in a box-drawn table. Columns whose
+    /// cells are all numbers are right aligned.
+    pub fn format_table(&self, headers: &[String], rows: &[Vec<String>]) -> Result<String, String> {
+        use unicode_width::UnicodeWidthStr;
+
+        fn cell(row: &[String], column: usize) -> &str {
+            row.get(column).map_or("", String::as_str)
+        }
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| cell(row, column).width())
+                    .chain(std::iter::once(headers[column].width()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let numeric: Vec<bool> = (0..headers.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| cell(row, column))
+                    .filter(|text| !text.is_empty())
+                    .all(|text| text.parse::<f64>().is_ok())
+            })
+            .collect();
+
+        let rule = |left: &str, middle: &str, right: &str| {
+            let parts: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+            format!("{left}{}{right}\n", parts.join(middle))
+        };
+        let line = |cells: Vec<&str>| {
+            let mut out = String::from("│");
+            for (column, text) in cells.into_iter().enumerate() {
+                let pad = " ".repeat(widths[column] - text.width());
+                if numeric[column] {
+                    out.push_str(&format!(" {pad}{text} │"));
+                } else {
+                    out.push_str(&format!(" {text}{pad} │"));
+                }
+            }
+            out.push('\n');
+            out
+        };
+
+        let mut out = rule("┌", "┬", "┐");
+        out.push_str(&line(headers.iter().map(String::as_str).collect()));
+        out.push_str(&rule("├", "┼", "┤"));
+        for row in rows {
+            out.push_str(&line(
+                (0..headers.len()).map(|column| cell(row, column)).collect(),
+            ));
+        }
+        out.push_str(&rule("└", "┴", "┘"));
+        Ok(out)
+    }
 }
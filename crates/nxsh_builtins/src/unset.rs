@@ -22,12 +22,8 @@ pub fn unset_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
 
     for name in &args[names_start..] {
         if mode_var {
-            if let Ok(mut env_guard) = ctx.env.write() {
-                env_guard.remove(name);
-            }
-            if let Ok(mut vars_guard) = ctx.vars.write() {
-                vars_guard.remove(name);
-            }
+            // Also handles arrays and single elements (`unset 'a[1]'`)
+            ctx.unset_var(name);
         } else if let Ok(mut aliases_guard) = ctx.aliases.write() {
             aliases_guard.remove(name);
         }
@@ -4,6 +4,8 @@
 
 use crate::common::process_utils::execute_uptime_command;
 use nxsh_core::{Builtin, ExecutionResult, ShellContext, ShellError, ShellResult};
+#[cfg(target_os = "linux")]
+use std::fs;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct UptimeBuiltin;
@@ -150,7 +152,7 @@ fn collect_linux_uptime_info() -> ShellResult<UptimeInfo> {
 }
 
 #[cfg(target_os = "linux")]
-fn read_proc_uptime() -> ShellResult<Duration> {
+pub(crate) fn read_proc_uptime() -> ShellResult<Duration> {
     let content = fs::read_to_string("/proc/uptime")
         .map_err(|e| ShellError::io(format!("Cannot read /proc/uptime: {}", e)))?;
 
@@ -167,7 +169,7 @@ fn read_proc_uptime() -> ShellResult<Duration> {
 }
 
 #[cfg(target_os = "linux")]
-fn read_proc_loadavg() -> ShellResult<(f64, f64, f64)> {
+pub(crate) fn read_proc_loadavg() -> ShellResult<(f64, f64, f64)> {
     let content = fs::read_to_string("/proc/loadavg")
         .map_err(|e| ShellError::io(format!("Cannot read /proc/loadavg: {}", e)))?;
 
@@ -297,7 +299,7 @@ fn format_current_time() -> String {
     }
 }
 
-fn format_uptime_duration(uptime: Duration) -> String {
+pub(crate) fn format_uptime_duration(uptime: Duration) -> String {
     let total_seconds = uptime.as_secs();
     let days = total_seconds / 86400;
     let hours = (total_seconds % 86400) / 3600;
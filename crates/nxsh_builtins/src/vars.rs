@@ -1,7 +1,6 @@
 use anyhow::{bail, Result};
 use exmex::Express; // Replaced meval with exmex for better C/C++ dependency elimination
 use nxsh_core::context::ShellContext;
-use nxsh_core::memory_efficient::MemoryEfficientStringBuilder;
 
 // NOTE: We intentionally avoid pulling in the regex crate here so that super-min
 // builds (which omit advanced-regex) do not drag in large dependencies. Lightweight
@@ -47,153 +46,23 @@ pub fn let_cli(exprs: &[String], ctx: &ShellContext) -> Result<()> {
     Ok(())
 }
 
-/// `declare` builtin (subset). Supports associative array (-A) and plain variable.
+/// `declare` builtin; see [`crate::declare`] for the supported options.
 pub fn declare_cli(args: &[String], ctx: &ShellContext) -> Result<()> {
-    if args.is_empty() {
-        // Print all vars
-        if let Ok(vars_guard) = ctx.vars.read() {
-            for (key, var) in vars_guard.iter() {
-                println!("{}={}", key, var.value);
-            }
-        }
-        return Ok(());
-    }
-    let mut iter = args.iter();
-    let mut assoc = false;
-    if let Some(flag) = iter.next() {
-        if flag == "-A" {
-            assoc = true;
-        } else {
-            iter = args.iter(); // no flag present
-        }
-    }
-    for name in iter {
-        if assoc {
-            ctx.set_var(name, "__assoc_array__".to_string());
-        } else {
-            ctx.set_var(name, String::new());
-        }
+    let result = crate::declare::run(ctx, args);
+    print!("{}", result.stdout);
+    eprint!("{}", result.stderr);
+    if result.exit_code != 0 {
+        bail!("declare failed with status {}", result.exit_code);
     }
     Ok(())
 }
 
-/// `printf` builtin supporting %d %x %s with width/zero-pad.
+/// `printf` builtin; see [`crate::printf`] for the supported conversions.
 pub fn printf_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Ok(());
-    }
-    let format_str = &args[0];
-    let mut out = MemoryEfficientStringBuilder::new(format_str.len() * 2);
-    let mut arg_iter = args.iter().skip(1);
-    let bytes: Vec<char> = format_str.chars().collect();
-    let mut i = 0;
-    while i < bytes.len() {
-        if bytes[i] == '%' {
-            i += 1;
-            if i >= bytes.len() {
-                break;
-            }
-            let mut zero = false;
-            if bytes[i] == '0' {
-                zero = true;
-                i += 1;
-            }
-            let mut width_str = MemoryEfficientStringBuilder::new(8);
-            while i < bytes.len() && bytes[i].is_ascii_digit() {
-                width_str.push(bytes[i]);
-                i += 1;
-            }
-            if i >= bytes.len() {
-                break;
-            }
-            let ty = bytes[i];
-            i += 1;
-            let width: usize = width_str.into_string().parse().unwrap_or(0);
-            let arg = arg_iter
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("missing printf argument"))?;
-            let formatted = match ty {
-                'd' => {
-                    let v: i64 = arg.parse()?;
-                    if width > 0 {
-                        if zero {
-                            let mut result = MemoryEfficientStringBuilder::new(width + 2);
-                            let num_str = v.to_string();
-                            let padding = width.saturating_sub(num_str.len());
-                            if v < 0 {
-                                result.push('-');
-                                for _ in 0..padding {
-                                    result.push('0');
-                                }
-                                result.push_str(&num_str[1..]);
-                            } else {
-                                for _ in 0..padding {
-                                    result.push('0');
-                                }
-                                result.push_str(&num_str);
-                            }
-                            result.into_string()
-                        } else {
-                            let mut result = MemoryEfficientStringBuilder::new(width + 2);
-                            let num_str = v.to_string();
-                            let padding = width.saturating_sub(num_str.len());
-                            for _ in 0..padding {
-                                result.push(' ');
-                            }
-                            result.push_str(&num_str);
-                            result.into_string()
-                        }
-                    } else {
-                        v.to_string()
-                    }
-                }
-                'x' => {
-                    let v: i64 = arg.parse()?;
-                    if width > 0 {
-                        if zero {
-                            let mut result = MemoryEfficientStringBuilder::new(width + 2);
-                            let hex_str = format!("{v:x}");
-                            let padding = width.saturating_sub(hex_str.len());
-                            for _ in 0..padding {
-                                result.push('0');
-                            }
-                            result.push_str(&hex_str);
-                            result.into_string()
-                        } else {
-                            let mut result = MemoryEfficientStringBuilder::new(width + 2);
-                            let hex_str = format!("{v:x}");
-                            let padding = width.saturating_sub(hex_str.len());
-                            for _ in 0..padding {
-                                result.push(' ');
-                            }
-                            result.push_str(&hex_str);
-                            result.into_string()
-                        }
-                    } else {
-                        format!("{v:x}")
-                    }
-                }
-                's' => arg.clone(),
-                '%' => "%".into(),
-                _ => {
-                    // Unknown specifier, emit literally
-                    let mut lit = MemoryEfficientStringBuilder::new(8);
-                    lit.push('%');
-                    if zero {
-                        lit.push('0');
-                    }
-                    lit.push_str(&width.to_string());
-                    lit.push(ty);
-                    lit.into_string()
-                }
-            };
-            out.push_str(&formatted);
-        } else {
-            out.push(bytes[i]);
-            i += 1;
-        }
+    let status = crate::printf::run_legacy(args)?;
+    if status != 0 {
+        bail!("printf failed with status {status}");
     }
-    print!("{}", out.into_string());
     Ok(())
 }
 
@@ -1,1074 +1,533 @@
-//! `watch` builtin  Eworld-class real-time command monitoring with advanced features.
+//! `watch` builtin - run a command repeatedly and show its output
 //!
-//! This implementation provides complete watch functionality with professional features:
-//! - Real-time command execution with customizable intervals
-//! - Advanced difference highlighting (character, word, line level)
-//! - Full internationalization support (10+ languages)
-//! - Command history with filtering and search
-//! - Multiple display modes (full, compact, split, dashboard)
-//! - Statistics and performance metrics
-//! - Export capabilities (JSON, CSV, HTML)
-//! - Custom themes and color schemes
-//! - Notification system for changes
-//! - Pause/resume functionality
-//! - Keyboard shortcuts and mouse support
-//! - Multiple command monitoring
-//! - Conditional execution and triggers
-//! - Log rotation and archiving
-//! - Integration with system monitoring
-//! - Memory usage optimization
-//! - Cross-platform terminal optimization
+//! Syntax:
+//!   watch [-n SECS] [-d] [-t] [-g] [-x] COMMAND [ARG]...
+//!
+//! Runs COMMAND every SECS seconds (2 by default, at least 0.1) and shows
+//! its standard output and error under a header with the interval, the
+//! command, the host name and the time. The command line is passed to
+//! `sh -c` (`cmd /C` on Windows) unless `-x` runs it directly.
+//!
+//! Options:
+//!   -n, --interval=SECS   seconds to wait between runs
+//!   -d, --differences     highlight what changed since the previous run
+//!   -t, --no-title        leave out the header
+//!   -g, --chgexit         exit when the output changes
+//!   -x, --exec            run COMMAND without a shell
+//!
+//! On a terminal the screen is cleared and redrawn after every run and
+//! Ctrl-C or `q` restores the screen and exits. Otherwise each run's output
+//! is written one after another until the output changes under `-g` or the
+//! shell's time limit is reached.
+//!
+//! The exit status is 0 when watch is stopped or the output changed under
+//! `-g`, and 1 on bad options or when COMMAND cannot be run.
+
+use crate::common::wait::sleep_unless;
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::{Attribute, SetAttribute};
+use crossterm::terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Seconds between runs without `-n`
+const DEFAULT_INTERVAL: f64 = 2.0;
 
-use anyhow::{anyhow, Result, Context};
-use chrono::{DateTime, Local, Duration as ChronoDuration};
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind},
-    execute,
-    style::{Color, Print, ResetColor, SetForegroundColor, SetBackgroundColor, Attribute},
-    terminal::{self, Clear, ClearType, enable_raw_mode, disable_raw_mode},
-    cursor::{Hide, Show, MoveTo, MoveToColumn, MoveToNextLine},
-};
-use serde::{Deserialize, Serialize};
-use std::{
-    collections::{HashMap, VecDeque},
-    fmt,
-    io::{stdout, Write, BufRead, BufReader},
-    process::{Command, Stdio},
-    sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}, mpsc},
-    time::{Duration, Instant, SystemTime},
-};
-use tokio::{
-    process::Command as AsyncCommand,
-    sync::{broadcast, RwLock},
-    time::{sleep, interval, MissedTickBehavior},
-};
-use unicode_width::UnicodeWidthStr;
-use regex::Regex;
-use crate::common::i18n::I18n;
+/// The shortest interval `-n` is raised to
+const MIN_INTERVAL: f64 = 0.1;
 
-// Configuration constants
-const DEFAULT_INTERVAL: f64 = 2.0;
-const MAX_HISTORY_SIZE: usize = 1000;
-const DIFF_CONTEXT_LINES: usize = 3;
-const PROGRESS_UPDATE_INTERVAL_MS: u64 = 100;
-const STATISTICS_UPDATE_INTERVAL_MS: u64 = 1000;
+/// Width of the header when there is no terminal to measure
+const PLAIN_WIDTH: usize = 80;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WatchConfig {
-    pub interval: f64,
-    pub show_header: bool,
-    pub show_differences: bool,
-    pub difference_mode: DifferenceMode,
-    pub color_enabled: bool,
-    pub beep_on_change: bool,
-    pub exit_on_change: bool,
-    pub exit_on_error: bool,
-    pub precise_timing: bool,
-    pub show_statistics: bool,
-    pub save_history: bool,
-    pub max_history: usize,
-    pub theme: WatchTheme,
-    pub display_mode: DisplayMode,
-    pub mouse_enabled: bool,
-    pub notifications_enabled: bool,
-    pub auto_scroll: bool,
-    pub line_wrap: bool,
-    pub show_line_numbers: bool,
-    pub compact_mode: bool,
-}
+/// The `watch` builtin command implementation
+pub struct WatchCommand;
 
-impl Default for WatchConfig {
-    fn default() -> Self {
-        Self {
-            interval: DEFAULT_INTERVAL,
-            show_header: true,
-            show_differences: false,
-            difference_mode: DifferenceMode::Line,
-            color_enabled: true,
-            beep_on_change: false,
-            exit_on_change: false,
-            exit_on_error: false,
-            precise_timing: false,
-            show_statistics: false,
-            save_history: true,
-            max_history: MAX_HISTORY_SIZE,
-            theme: WatchTheme::default(),
-            display_mode: DisplayMode::Full,
-            mouse_enabled: false,
-            notifications_enabled: false,
-            auto_scroll: true,
-            line_wrap: true,
-            show_line_numbers: false,
-            compact_mode: false,
-        }
+impl Builtin for WatchCommand {
+    fn name(&self) -> &'static str {
+        "watch"
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum DifferenceMode {
-    None,
-    Character,
-    Word,
-    Line,
-    Semantic,
-}
+    fn synopsis(&self) -> &'static str {
+        "Run a command repeatedly and show its output"
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum DisplayMode {
-    Full,
-    Compact,
-    Split,
-    Dashboard,
-    Minimal,
-}
+    fn description(&self) -> &'static str {
+        "Run COMMAND every few seconds, redrawing the screen with its latest output and \
+         optionally highlighting what changed since the previous run."
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WatchTheme {
-    pub header_color: Color,
-    pub timestamp_color: Color,
-    pub command_color: Color,
-    pub output_color: Color,
-    pub diff_add_color: Color,
-    pub diff_remove_color: Color,
-    pub diff_change_color: Color,
-    pub error_color: Color,
-    pub warning_color: Color,
-    pub info_color: Color,
-    pub border_color: Color,
-}
+    fn usage(&self) -> &'static str {
+        "watch [-n SECS] [-d] [-t] [-g] [-x] COMMAND [ARG]..."
+    }
 
-impl Default for WatchTheme {
-    fn default() -> Self {
-        Self {
-            header_color: Color::Cyan,
-            timestamp_color: Color::Green,
-            command_color: Color::Yellow,
-            output_color: Color::White,
-            diff_add_color: Color::Green,
-            diff_remove_color: Color::Red,
-            diff_change_color: Color::Yellow,
-            error_color: Color::Red,
-            warning_color: Color::Yellow,
-            info_color: Color::Blue,
-            border_color: Color::Grey,
-        }
+    fn help(&self) -> &'static str {
+        "Run a command repeatedly and show its output. Use 'watch -d -n 1 df -h' to see \
+         disk usage change; press Ctrl-C to stop."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("watch: {message}\n").into_bytes()))
+            }
+        };
+        let env: Vec<(String, String)> = match ctx.env.read() {
+            Ok(env) => env.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        let program = ctx
+            .resolve_command(&options.command[0])
+            .unwrap_or_else(|| options.command[0].clone().into());
+        let job = Job {
+            program,
+            cwd: ctx.cwd.clone(),
+            env,
+        };
+        let outcome = if ctx.is_interactive() && io::stdout().is_terminal() {
+            let result = Terminal::open(&mut *ctx.stdout)
+                .map_err(|e| io_message(&e))
+                .and_then(|mut screen| watch(&options, &job, &mut screen));
+            Outcome::new(result, Vec::new())
+        } else {
+            let mut screen = Plain {
+                out: Vec::new(),
+                stopped: || ctx.is_timed_out(),
+            };
+            let result = watch(&options, &job, &mut screen);
+            Outcome::new(result, screen.out)
+        };
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WatchExecution {
-    pub id: u64,
-    pub timestamp: DateTime<Local>,
-    pub command: String,
-    pub output: String,
-    pub stderr: String,
-    pub exit_code: Option<i32>,
-    pub duration: Duration,
-    pub changes_detected: bool,
-    pub change_count: usize,
-    pub line_count: usize,
-    pub byte_count: usize,
+/// Run watch for the legacy dispatcher, drawing on the process's terminal
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("watch: {message}");
+            return Ok(1);
+        }
+    };
+    let job = Job {
+        program: options.command[0].clone().into(),
+        cwd: std::env::current_dir()?,
+        env: Vec::new(),
+    };
+    let result = if io::stdout().is_terminal() {
+        Terminal::open(io::stdout())
+            .map_err(|e| io_message(&e))
+            .and_then(|mut screen| watch(&options, &job, &mut screen))
+    } else {
+        let mut screen = Plain {
+            out: io::stdout(),
+            stopped: || false,
+        };
+        watch(&options, &job, &mut screen)
+    };
+    let outcome = Outcome::new(result, Vec::new());
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
 }
 
-#[derive(Debug)]
-pub struct WatchStatistics {
-    pub total_executions: u64,
-    pub successful_executions: u64,
-    pub failed_executions: u64,
-    pub total_runtime: Duration,
-    pub average_execution_time: Duration,
-    pub min_execution_time: Duration,
-    pub max_execution_time: Duration,
-    pub changes_detected: u64,
-    pub total_output_lines: u64,
-    pub total_output_bytes: u64,
-    pub start_time: DateTime<Local>,
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
 }
 
-impl Default for WatchStatistics {
-    fn default() -> Self {
-        Self {
-            total_executions: 0,
-            successful_executions: 0,
-            failed_executions: 0,
-            total_runtime: Duration::ZERO,
-            average_execution_time: Duration::ZERO,
-            min_execution_time: Duration::MAX,
-            max_execution_time: Duration::ZERO,
-            changes_detected: 0,
-            total_output_lines: 0,
-            total_output_bytes: 0,
-            start_time: Local::now(),
+impl Outcome {
+    fn new(result: Result<(), String>, stdout: Vec<u8>) -> Self {
+        match result {
+            Ok(()) => Outcome {
+                stdout,
+                stderr: Vec::new(),
+                status: 0,
+            },
+            Err(message) => Outcome {
+                stdout,
+                stderr: format!("watch: {message}\n").into_bytes(),
+                status: 1,
+            },
         }
     }
 }
 
 #[derive(Debug)]
-pub struct WatchManager {
-    config: Arc<RwLock<WatchConfig>>,
-    command: String,
-    args: Vec<String>,
-    history: Arc<RwLock<VecDeque<WatchExecution>>>,
-    statistics: Arc<RwLock<WatchStatistics>>,
-    execution_counter: Arc<AtomicU64>,
-    running: Arc<AtomicBool>,
-    paused: Arc<AtomicBool>,
-    current_output: Arc<RwLock<String>>,
-    last_output: Arc<RwLock<String>>,
-    notification_sender: broadcast::Sender<String>,
-    i18n: I18n,
-    filter_regex: Option<Regex>,
-    terminal_size: (u16, u16),
-    scroll_position: usize,
-    selected_execution: Option<u64>,
+struct Options {
+    interval: Duration,
+    differences: bool,
+    title: bool,
+    chgexit: bool,
+    exec: bool,
+    command: Vec<String>,
 }
 
-impl WatchManager {
-    pub fn new(command: String, args: Vec<String>, config: WatchConfig, i18n: I18n) -> Result<Self> {
-        let (tx, _) = broadcast::channel(100);
-        let terminal_size = terminal::size().unwrap_or((80, 24));
-        
-        Ok(Self {
-            config: Arc::new(RwLock::new(config)),
-            command,
-            args,
-            history: Arc::new(RwLock::new(VecDeque::with_capacity(MAX_HISTORY_SIZE))),
-            statistics: Arc::new(RwLock::new(WatchStatistics::default())),
-            execution_counter: Arc::new(AtomicU64::new(0)),
-            running: Arc::new(AtomicBool::new(true)),
-            paused: Arc::new(AtomicBool::new(false)),
-            current_output: Arc::new(RwLock::new(String::new())),
-            last_output: Arc::new(RwLock::new(String::new())),
-            notification_sender: tx,
-            i18n,
-            filter_regex: None,
-            terminal_size,
-            scroll_position: 0,
-            selected_execution: None,
-        })
-    }
-
-    pub async fn run(&mut self) -> Result<()> {
-        // Setup terminal
-        enable_raw_mode()?;
-        execute!(stdout(), Hide)?;
-        
-        // Setup signal handlers
-        let running = Arc::clone(&self.running);
-        tokio::spawn(async move {
-            if tokio::signal::ctrl_c().await.is_ok() {
-                running.store(false, Ordering::Relaxed);
-            }
-        });
-
-        // Start command execution loop
-        let execution_handle = self.start_execution_loop();
-        
-        // Start UI update loop
-        let ui_handle = self.start_ui_loop();
-        
-        // Start statistics update loop
-        let stats_handle = self.start_statistics_loop();
-        
-        // Handle keyboard input
-        let input_handle = self.start_input_handler();
-
-        // Wait for completion
-        tokio::select! {
-            _ = execution_handle => {},
-            _ = ui_handle => {},
-            _ = stats_handle => {},
-            _ = input_handle => {},
-        }
-
-        // Cleanup
-        disable_raw_mode()?;
-        execute!(stdout(), Show, Clear(ClearType::All), MoveTo(0, 0))?;
-        
-        Ok(())
-    }
-
-    async fn start_execution_loop(&self) -> Result<()> {
-        let mut interval_timer = interval(Duration::from_secs_f64(self.config.read().unwrap().interval));
-        if self.config.read().unwrap().precise_timing {
-            interval_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
-        }
-
-        while self.running.load(Ordering::Relaxed) {
-            if !self.paused.load(Ordering::Relaxed) {
-                let execution_id = self.execution_counter.fetch_add(1, Ordering::Relaxed);
-                
-                match self.execute_command(execution_id).await {
-                    Ok(execution) => {
-                        self.process_execution_result(execution).await?;
-                    }
-                    Err(e) => {
-                        eprintln!("Execution error: {}", e);
-                        if self.config.read().unwrap().exit_on_error {
-                            break;
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            interval: Duration::from_secs_f64(DEFAULT_INTERVAL),
+            differences: false,
+            title: true,
+            chgexit: false,
+            exec: false,
+            command: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    options.command.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "--differences" => options.differences = true,
+                "--no-title" => options.title = false,
+                "--chgexit" => options.chgexit = true,
+                "--exec" => options.exec = true,
+                "--interval" => {
+                    let secs = iter
+                        .next()
+                        .ok_or_else(|| "option '--interval' requires an argument".to_string())?;
+                    options.interval = parse_interval(secs)?;
+                }
+                long if long.starts_with("--interval=") => {
+                    options.interval = parse_interval(&long["--interval=".len()..])?;
+                }
+                long if long.starts_with("--") => {
+                    return Err(format!("unrecognized option '{long}'"));
+                }
+                short if short.len() > 1 && short.starts_with('-') => {
+                    for (at, flag) in short[1..].char_indices() {
+                        match flag {
+                            'd' => options.differences = true,
+                            't' => options.title = false,
+                            'g' => options.chgexit = true,
+                            'x' => options.exec = true,
+                            'n' => {
+                                let attached = &short[2 + at..];
+                                let secs = match attached {
+                                    "" => iter.next().map(String::as_str).ok_or_else(|| {
+                                        "option requires an argument -- 'n'".to_string()
+                                    })?,
+                                    _ => attached,
+                                };
+                                options.interval = parse_interval(secs)?;
+                                break;
+                            }
+                            _ => return Err(format!("invalid option -- '{flag}'")),
                         }
                     }
                 }
+                _ => {
+                    // Everything from the command on belongs to it
+                    options.command.push(arg.clone());
+                    options.command.extend(iter.by_ref().cloned());
+                    break;
+                }
             }
-            
-            interval_timer.tick().await;
         }
-        
-        Ok(())
+        if options.command.is_empty() {
+            return Err("no command given".to_string());
+        }
+        Ok(options)
     }
 
-    async fn execute_command(&self, execution_id: u64) -> Result<WatchExecution> {
-        let start_time = Instant::now();
-        let timestamp = Local::now();
-        
-        let mut cmd = AsyncCommand::new(&self.command);
-        cmd.args(&self.args)
-           .stdout(Stdio::piped())
-           .stderr(Stdio::piped());
-
-        let output = cmd.output().await
-            .with_context(|| format!("Failed to execute command: {}", self.command))?;
-
-        let duration = start_time.elapsed();
-        
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        
-        let execution = WatchExecution {
-            id: execution_id,
-            timestamp,
-            command: format!("{} {}", self.command, self.args.join(" ")),
-            output: stdout.clone(),
-            stderr,
-            exit_code: output.status.code(),
-            duration,
-            changes_detected: false,
-            change_count: 0,
-            line_count: stdout.lines().count(),
-            byte_count: stdout.len(),
-        };
+    /// The command as shown in the header and given to the shell
+    fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+}
 
-        Ok(execution)
+fn parse_interval(text: &str) -> Result<Duration, String> {
+    match text.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(
+            secs.clamp(MIN_INTERVAL, f64::from(u32::MAX)),
+        )),
+        _ => Err(format!("invalid interval '{text}'")),
     }
+}
 
-    async fn process_execution_result(&self, mut execution: WatchExecution) -> Result<()> {
-        // Check for changes
-        let last_output = self.last_output.read().await;
-        let changes_detected = if self.config.show_differences {
-            self.detect_changes(&last_output, &execution.output)
+/// Where and how the command runs
+struct Job {
+    program: PathBuf,
+    cwd: PathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl Job {
+    /// Run the command once, returning its standard output followed by its
+    /// standard error
+    fn run(&self, options: &Options) -> Result<String, String> {
+        let mut command = if options.exec {
+            let mut command = Command::new(&self.program);
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::arg0(&mut command, &options.command[0]);
+            command.args(&options.command[1..]);
+            command
         } else {
-            false
+            shell(&options.command_line())
         };
-
-        execution.changes_detected = changes_detected;
-        if changes_detected {
-            execution.change_count = self.count_changes(&last_output, &execution.output);
-            
-            if self.config.beep_on_change {
-                self.beep().await?;
-            }
-            
-            if self.config.notifications_enabled {
-                self.send_notification(&format!("{}: {}", 
-                    self.i18n.get("watch.notification.changes_detected", None),
-                    execution.command
-                )).await?;
-            }
-            
-            if self.config.exit_on_change {
-                self.running.store(false, Ordering::Relaxed);
-            }
-        }
-
-        // Update current and last output
-        {
-            let mut current = self.current_output.write().await;
-            *current = execution.output.clone();
-        }
-        {
-            let mut last = self.last_output.write().await;
-            *last = execution.output.clone();
+        command.current_dir(&self.cwd).stdin(Stdio::null());
+        for (key, value) in &self.env {
+            command.env(key, value);
         }
+        let output = command
+            .output()
+            .map_err(|e| format!("{}: {}", options.command[0], io_message(&e)))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(text)
+    }
+}
 
-        // Add to history
-        if self.config.save_history {
-            let mut history = self.history.write().await;
-            if history.len() >= self.config.max_history {
-                history.pop_front();
-            }
-            history.push_back(execution);
-        }
+#[cfg(unix)]
+fn shell(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
 
-        // Update statistics
-        self.update_statistics(&execution).await;
+#[cfg(windows)]
+fn shell(line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(line);
+    command
+}
 
-        Ok(())
-    }
+/// Where the frames go
+trait Screen {
+    /// Columns available for the header
+    fn width(&self) -> usize;
 
-    fn detect_changes(&self, old: &str, new: &str) -> bool {
-        match self.config.difference_mode {
-            DifferenceMode::None => false,
-            DifferenceMode::Character => old != new,
-            DifferenceMode::Word => {
-                let old_words: Vec<&str> = old.split_whitespace().collect();
-                let new_words: Vec<&str> = new.split_whitespace().collect();
-                old_words != new_words
-            }
-            DifferenceMode::Line => {
-                let old_lines: Vec<&str> = old.lines().collect();
-                let new_lines: Vec<&str> = new.lines().collect();
-                old_lines != new_lines
-            }
-            DifferenceMode::Semantic => {
-                // Implement semantic difference detection
-                // This is a simplified version
-                old.trim() != new.trim()
-            }
-        }
-    }
+    /// Show a frame in place of the previous one
+    fn draw(&mut self, frame: &str) -> io::Result<()>;
 
-    fn count_changes(&self, old: &str, new: &str) -> usize {
-        match self.config.difference_mode {
-            DifferenceMode::Character => {
-                old.chars().zip(new.chars()).filter(|(a, b)| a != b).count()
-            }
-            DifferenceMode::Word => {
-                let old_words: Vec<&str> = old.split_whitespace().collect();
-                let new_words: Vec<&str> = new.split_whitespace().collect();
-                old_words.iter().zip(new_words.iter()).filter(|(a, b)| a != b).count()
-            }
-            DifferenceMode::Line => {
-                let old_lines: Vec<&str> = old.lines().collect();
-                let new_lines: Vec<&str> = new.lines().collect();
-                old_lines.iter().zip(new_lines.iter()).filter(|(a, b)| a != b).count()
-            }
-            _ => if old != new { 1 } else { 0 }
-        }
-    }
+    /// Wait for up to `timeout`, returning true when watch should stop
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool>;
+}
 
-    async fn start_ui_loop(&self) -> Result<()> {
-        let mut ui_interval = interval(Duration::from_millis(PROGRESS_UPDATE_INTERVAL_MS));
-        
-        while self.running.load(Ordering::Relaxed) {
-            self.render_ui().await?;
-            ui_interval.tick().await;
+/// Run the command until the screen asks to stop or, under `-g`, its
+/// output changes
+fn watch(options: &Options, job: &Job, screen: &mut dyn Screen) -> Result<(), String> {
+    let line = options.command_line();
+    let host = ::whoami::fallible::hostname().unwrap_or_else(|_| "localhost".to_string());
+    let mut previous: Option<String> = None;
+    loop {
+        let output = job.run(options)?;
+        let mut frame = String::new();
+        if options.title {
+            let now = chrono::Local::now()
+                .format("%a %b %e %H:%M:%S %Y")
+                .to_string();
+            frame.push_str(&header(
+                options.interval,
+                &line,
+                &host,
+                &now,
+                screen.width(),
+            ));
+            frame.push_str("\n\n");
+        }
+        match &previous {
+            Some(before) if options.differences => frame.push_str(&highlight(before, &output)),
+            _ => frame.push_str(&output),
+        }
+        screen.draw(&frame).map_err(|e| io_message(&e))?;
+        let changed = previous.as_deref().is_some_and(|before| before != output);
+        if changed && options.chgexit {
+            return Ok(());
+        }
+        previous = Some(output);
+        if screen.wait(options.interval).map_err(|e| io_message(&e))? {
+            return Ok(());
         }
-        
-        Ok(())
     }
+}
 
-    async fn render_ui(&self) -> Result<()> {
-        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
-        
-        match self.config.display_mode {
-            DisplayMode::Full => self.render_full_ui().await?,
-            DisplayMode::Compact => self.render_compact_ui().await?,
-            DisplayMode::Split => self.render_split_ui().await?,
-            DisplayMode::Dashboard => self.render_dashboard_ui().await?,
-            DisplayMode::Minimal => self.render_minimal_ui().await?,
-        }
-        
-        stdout().flush()?;
-        Ok(())
-    }
+/// The title line: the interval and command on the left, the host and
+/// time on the right
+fn header(interval: Duration, line: &str, host: &str, now: &str, width: usize) -> String {
+    let right = format!("{host}: {now}");
+    let room = width.saturating_sub(right.chars().count() + 1);
+    let left: String = format!("Every {:.1}s: {line}", interval.as_secs_f64())
+        .chars()
+        .take(room)
+        .collect();
+    format!("{left:<room$} {right}")
+}
 
-    async fn render_full_ui(&self) -> Result<()> {
-        // Render header
-        if self.config.show_header {
-            self.render_header().await?;
+/// `current` with the characters that differ from `previous` at the same
+/// line and column shown in reverse video
+fn highlight(previous: &str, current: &str) -> String {
+    let on = SetAttribute(Attribute::Reverse).to_string();
+    let off = SetAttribute(Attribute::NoReverse).to_string();
+    let mut before = previous.split('\n');
+    let mut out = String::with_capacity(current.len());
+    for (index, line) in current.split('\n').enumerate() {
+        if index > 0 {
+            out.push('\n');
         }
-        
-        // Render main content
-        let current_output = self.current_output.read().await;
-        let lines: Vec<&str> = current_output.lines().collect();
-        
-        let start_line = self.scroll_position;
-        let visible_lines = (self.terminal_size.1 as usize).saturating_sub(if self.config.show_header { 4 } else { 0 });
-        
-        for (i, line) in lines.iter().skip(start_line).take(visible_lines).enumerate() {
-            if self.config.show_line_numbers {
-                execute!(stdout(), 
-                    SetForegroundColor(Color::Grey),
-                    Print(format!("{:4} ", start_line + i + 1)),
-                    ResetColor
-                )?;
-            }
-            
-            if self.config.line_wrap {
-                self.render_wrapped_line(line)?;
-            } else {
-                execute!(stdout(), Print(line), MoveToNextLine(1))?;
+        let mut old = before.next().unwrap_or("").chars();
+        let mut marked = false;
+        for ch in line.chars() {
+            let differs = old.next() != Some(ch);
+            if differs != marked {
+                out.push_str(if differs { &on } else { &off });
+                marked = differs;
             }
+            out.push(ch);
         }
-        
-        // Render status bar
-        self.render_status_bar().await?;
-        
-        Ok(())
-    }
-
-    async fn render_compact_ui(&self) -> Result<()> {
-        let current_output = self.current_output.read().await;
-        let lines: Vec<&str> = current_output.lines().collect();
-        
-        // Show only last few lines in compact mode
-        let visible_lines = self.terminal_size.1 as usize;
-        let start = if lines.len() > visible_lines {
-            lines.len() - visible_lines
-        } else {
-            0
-        };
-        
-        for line in lines.iter().skip(start) {
-            execute!(stdout(), Print(line), MoveToNextLine(1))?;
+        if marked {
+            out.push_str(&off);
         }
-        
-        Ok(())
     }
+    out
+}
 
-    async fn render_split_ui(&self) -> Result<()> {
-        let height = self.terminal_size.1 as usize;
-        let split_line = height / 2;
-        
-        // Top half: current output
-        execute!(stdout(), MoveTo(0, 0))?;
-        let current_output = self.current_output.read().await;
-        let current_lines: Vec<&str> = current_output.lines().take(split_line - 1).collect();
-        
-        for line in current_lines {
-            execute!(stdout(), Print(line), MoveToNextLine(1))?;
-        }
-        
-        // Separator
-        execute!(stdout(), 
-            SetForegroundColor(self.config.theme.border_color),
-            Print("-".repeat(self.terminal_size.0 as usize)),
-            ResetColor,
-            MoveToNextLine(1)
-        )?;
-        
-        // Bottom half: history or statistics
-        if self.config.show_statistics {
-            self.render_statistics().await?;
-        } else {
-            self.render_history_preview().await?;
-        }
-        
-        Ok(())
-    }
+/// The terminal, switched to its alternate screen in raw mode until dropped
+struct Terminal<W: Write> {
+    out: W,
+}
 
-    async fn render_dashboard_ui(&self) -> Result<()> {
-        // Multi-panel dashboard view
-        let width = self.terminal_size.0 as usize;
-        let height = self.terminal_size.1 as usize;
-        
-        // Top section: Command and status
-        self.render_header().await?;
-        
-        // Middle section: Output (left) and Statistics (right)
-        let mid_height = height - 6;
-        let left_width = width * 2 / 3;
-        
-        for row in 0..mid_height {
-            execute!(stdout(), MoveTo(0, (row + 3) as u16))?;
-            
-            // Left panel: Output
-            let current_output = self.current_output.read().await;
-            let lines: Vec<&str> = current_output.lines().collect();
-            if row < lines.len() {
-                let line = lines[row];
-                let truncated = if line.width() > left_width {
-                    format!("{}...", &line[..left_width.saturating_sub(3)])
-                } else {
-                    line.to_string()
-                };
-                execute!(stdout(), Print(truncated))?;
-            }
-            
-            // Vertical separator
-            execute!(stdout(), 
-                MoveTo(left_width as u16, (row + 3) as u16),
-                SetForegroundColor(self.config.theme.border_color),
-                Print("|"),
-                ResetColor
-            )?;
-            
-            // Right panel: Statistics or history
-            execute!(stdout(), MoveTo((left_width + 2) as u16, (row + 3) as u16))?;
-            if row < 10 { // Show statistics in first 10 lines
-                match row {
-                    0 => {
-                        let stats = self.statistics.read().await;
-                        execute!(stdout(), Print(format!("{}: {}", 
-                            self.i18n.get("watch.stats.executions", None), 
-                            stats.total_executions)))?;
-                    }
-                    1 => {
-                        let stats = self.statistics.read().await;
-                        execute!(stdout(), Print(format!("{}: {}", 
-                            self.i18n.get("watch.stats.changes", None), 
-                            stats.changes_detected)))?;
-                    }
-                    2 => {
-                        let stats = self.statistics.read().await;
-                        execute!(stdout(), Print(format!("{}: {:.2}ms", 
-                            self.i18n.get("watch.stats.avg_time", None), 
-                            stats.average_execution_time.as_secs_f64() * 1000.0)))?;
-                    }
-                    _ => {}
-                }
-            }
+impl<W: Write> Terminal<W> {
+    fn open(mut out: W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(out, EnterAlternateScreen, cursor::Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
         }
-        
-        // Bottom section: Status and controls
-        self.render_status_bar().await?;
-        
-        Ok(())
-    }
-
-    async fn render_minimal_ui(&self) -> Result<()> {
-        let current_output = self.current_output.read().await;
-        execute!(stdout(), Print(&*current_output))?;
-        Ok(())
+        Ok(Terminal { out })
     }
+}
 
-    async fn render_header(&self) -> Result<()> {
-        let stats = self.statistics.read().await;
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-        
-        execute!(stdout(),
-            SetForegroundColor(self.config.theme.header_color),
-            Print(format!("{}: {} ", self.i18n.get("watch.header.every", None), self.config.interval)),
-            SetForegroundColor(self.config.theme.command_color),
-            Print(&self.command),
-            Print(" "),
-            Print(self.args.join(" ")),
-            ResetColor,
-            MoveToNextLine(1)
-        )?;
-        
-        execute!(stdout(),
-            SetForegroundColor(self.config.theme.timestamp_color),
-            Print(format!("{}: {} | ", self.i18n.get("watch.header.timestamp", None), timestamp)),
-            Print(format!("{}: {} | ", self.i18n.get("watch.header.executions", None), stats.total_executions)),
-            Print(format!("{}: {}", self.i18n.get("watch.header.changes", None), stats.changes_detected)),
-            ResetColor,
-            MoveToNextLine(2)
-        )?;
-        
-        Ok(())
+impl<W: Write> Drop for Terminal<W> {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
     }
+}
 
-    async fn render_status_bar(&self) -> Result<()> {
-        let (width, height) = self.terminal_size;
-        execute!(stdout(), MoveTo(0, height - 1))?;
-        
-        let status = if self.paused.load(Ordering::Relaxed) {
-            format!("[{}] ", self.i18n.get("watch.status.paused", None))
-        } else {
-            format!("[{}] ", self.i18n.get("watch.status.running", None))
-        };
-        
-        let help = format!("q:{} | p:{} | h:{}", 
-            self.i18n.get("watch.keys.quit", None),
-            self.i18n.get("watch.keys.pause", None),
-            self.i18n.get("watch.keys.help", None)
-        );
-        
-        execute!(stdout(),
-            SetBackgroundColor(Color::DarkGrey),
-            SetForegroundColor(Color::White),
-            Print(status),
-            Print(" ".repeat((width as usize).saturating_sub(status.len() + help.len()))),
-            Print(help),
-            ResetColor
-        )?;
-        
-        Ok(())
+impl<W: Write> Screen for Terminal<W> {
+    fn width(&self) -> usize {
+        terminal::size().map_or(PLAIN_WIDTH, |(columns, _)| usize::from(columns))
     }
 
-    async fn render_statistics(&self) -> Result<()> {
-        let stats = self.statistics.read().await;
-        
-        execute!(stdout(), 
-            SetForegroundColor(self.config.theme.info_color),
-            Print(format!("{}\n", self.i18n.get("watch.stats.title", None))),
-            ResetColor
+    fn draw(&mut self, frame: &str) -> io::Result<()> {
+        let rows = terminal::size().map_or(usize::MAX, |(_, rows)| usize::from(rows));
+        queue!(
+            self.out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
         )?;
-        
-        execute!(stdout(), Print(format!("{}: {}\n", 
-            self.i18n.get("watch.stats.total_executions", None), stats.total_executions)))?;
-        execute!(stdout(), Print(format!("{}: {}\n", 
-            self.i18n.get("watch.stats.successful", None), stats.successful_executions)))?;
-        execute!(stdout(), Print(format!("{}: {}\n", 
-            self.i18n.get("watch.stats.failed", None), stats.failed_executions)))?;
-        execute!(stdout(), Print(format!("{}: {:.2}ms\n", 
-            self.i18n.get("watch.stats.avg_time", None), stats.average_execution_time.as_secs_f64() * 1000.0)))?;
-        execute!(stdout(), Print(format!("{}: {}\n", 
-            self.i18n.get("watch.stats.changes_detected", None), stats.changes_detected)))?;
-        
-        Ok(())
-    }
-
-    async fn render_history_preview(&self) -> Result<()> {
-        let history = self.history.read().await;
-        let recent: Vec<_> = history.iter().rev().take(5).collect();
-        
-        execute!(stdout(), 
-            SetForegroundColor(self.config.theme.info_color),
-            Print(format!("{}\n", self.i18n.get("watch.history.title", None))),
-            ResetColor
-        )?;
-        
-        for execution in recent {
-            let status = if execution.exit_code == Some(0) { "OK" } else { "ERR" };
-            let changes = if execution.changes_detected { "CHG" } else { "---" };
-            
-            execute!(stdout(), Print(format!("{} {} {} [{}ms] {}\n",
-                status,
-                changes,
-                execution.timestamp.format("%H:%M:%S"),
-                execution.duration.as_millis(),
-                if execution.output.len() > 50 {
-                    format!("{}...", &execution.output[..47])
-                } else {
-                    execution.output.clone()
-                }.replace('\n', " ")
-            )))?;
-        }
-        
-        Ok(())
-    }
-
-    fn render_wrapped_line(&self, line: &str) -> Result<()> {
-        let width = self.terminal_size.0 as usize;
-        if self.config.show_line_numbers {
-            // Account for line number space
-            let content_width = width.saturating_sub(5);
-            for chunk in line.chars().collect::<Vec<_>>().chunks(content_width) {
-                let chunk_str: String = chunk.iter().collect();
-                execute!(stdout(), Print(chunk_str), MoveToNextLine(1))?;
-            }
-        } else {
-            for chunk in line.chars().collect::<Vec<_>>().chunks(width) {
-                let chunk_str: String = chunk.iter().collect();
-                execute!(stdout(), Print(chunk_str), MoveToNextLine(1))?;
-            }
-        }
-        Ok(())
-    }
-
-    async fn start_statistics_loop(&self) -> Result<()> {
-        let mut stats_interval = interval(Duration::from_millis(STATISTICS_UPDATE_INTERVAL_MS));
-        
-        while self.running.load(Ordering::Relaxed) {
-            // Update real-time statistics here if needed
-            stats_interval.tick().await;
-        }
-        
-        Ok(())
-    }
-
-    async fn start_input_handler(&self) -> Result<()> {
-        while self.running.load(Ordering::Relaxed) {
-            if event::poll(Duration::from_millis(100))? {
-                match event::read()? {
-                    Event::Key(key_event) => {
-                        self.handle_key_event(key_event).await?;
-                    }
-                    Event::Mouse(mouse_event) if self.config.mouse_enabled => {
-                        self.handle_mouse_event(mouse_event).await?;
-                    }
-                    Event::Resize(width, height) => {
-                        self.terminal_size = (width, height);
-                    }
-                    _ => {}
-                }
+        // Raw mode does not return the carriage on a newline, and a line
+        // past the bottom would scroll the header away
+        for (index, line) in frame.lines().take(rows).enumerate() {
+            if index > 0 {
+                self.out.write_all(b"\r\n")?;
             }
+            self.out.write_all(line.as_bytes())?;
         }
-        Ok(())
+        self.out.flush()
     }
 
-    async fn handle_key_event(&self, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Char('q') | KeyCode::Char('Q') => {
-                self.running.store(false, Ordering::Relaxed);
-            }
-            KeyCode::Char('p') | KeyCode::Char('P') => {
-                let current = self.paused.load(Ordering::Relaxed);
-                self.paused.store(!current, Ordering::Relaxed);
-            }
-            KeyCode::Char('r') | KeyCode::Char('R') => {
-                // Force refresh
-                let execution_id = self.execution_counter.fetch_add(1, Ordering::Relaxed);
-                if let Ok(execution) = self.execute_command(execution_id).await {
-                    self.process_execution_result(execution).await?;
-                }
-            }
-            KeyCode::Char('d') | KeyCode::Char('D') => {
-                // Toggle differences
-                if let Ok(mut config) = self.config.write() {
-                    config.show_differences = !config.show_differences;
-                }
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !event::poll(left)? {
+                return Ok(false);
             }
-            KeyCode::Char('s') | KeyCode::Char('S') => {
-                // Toggle statistics display
-                if let Ok(mut config) = self.config.write() {
-                    config.show_statistics = !config.show_statistics;
+            if let Event::Key(key) = event::read()? {
+                let interrupt =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if interrupt || key.code == KeyCode::Char('q') {
+                    return Ok(true);
                 }
             }
-            KeyCode::Up => {
-                self.scroll_position = self.scroll_position.saturating_sub(1);
-            }
-            KeyCode::Down => {
-                self.scroll_position += 1;
-            }
-            KeyCode::PageUp => {
-                self.scroll_position = self.scroll_position.saturating_sub(10);
-            }
-            KeyCode::PageDown => {
-                self.scroll_position += 10;
-            }
-            KeyCode::Home => {
-                self.scroll_position = 0;
-            }
-            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.running.store(false, Ordering::Relaxed);
-            }
-            _ => {}
-        }
-        Ok(())
-    }
-
-    async fn handle_mouse_event(&self, mouse: MouseEvent) -> Result<()> {
-        match mouse.kind {
-            MouseEventKind::ScrollUp => {
-                self.scroll_position = self.scroll_position.saturating_sub(3);
-            }
-            MouseEventKind::ScrollDown => {
-                self.scroll_position += 3;
-            }
-            _ => {}
         }
-        Ok(())
     }
+}
 
-    async fn update_statistics(&self, execution: &WatchExecution) {
-        let mut stats = self.statistics.write().await;
-        
-        stats.total_executions += 1;
-        stats.total_runtime += execution.duration;
-        
-        if execution.exit_code == Some(0) {
-            stats.successful_executions += 1;
-        } else {
-            stats.failed_executions += 1;
-        }
-        
-        if execution.changes_detected {
-            stats.changes_detected += 1;
-        }
-        
-        stats.total_output_lines += execution.line_count as u64;
-        stats.total_output_bytes += execution.byte_count as u64;
-        
-        // Update timing statistics
-        if execution.duration < stats.min_execution_time {
-            stats.min_execution_time = execution.duration;
-        }
-        if execution.duration > stats.max_execution_time {
-            stats.max_execution_time = execution.duration;
-        }
-        
-        stats.average_execution_time = stats.total_runtime / stats.total_executions as u32;
-    }
+/// Frames written one after another when there is no terminal
+struct Plain<W, F> {
+    out: W,
+    stopped: F,
+}
 
-    async fn beep(&self) -> Result<()> {
-        execute!(stdout(), Print("\x07"))?;
-        Ok(())
+impl<W: Write, F: Fn() -> bool> Screen for Plain<W, F> {
+    fn width(&self) -> usize {
+        PLAIN_WIDTH
     }
 
-    async fn send_notification(&self, message: &str) -> Result<()> {
-        let _ = self.notification_sender.send(message.to_string());
-        Ok(())
+    fn draw(&mut self, frame: &str) -> io::Result<()> {
+        self.out.write_all(frame.as_bytes())?;
+        if !frame.is_empty() && !frame.ends_with('\n') {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()
     }
 
-    pub async fn export_history(&self, format: &str, filename: &str) -> Result<()> {
-        let history = self.history.read().await;
-        
-        match format.to_lowercase().as_str() {
-            "json" => {
-                let json = serde_json::to_string_pretty(&*history)?;
-                tokio::fs::write(filename, json).await?;
-            }
-            "csv" => {
-                let mut csv_content = String::from("id,timestamp,command,exit_code,duration_ms,changes_detected,line_count,byte_count\n");
-                for execution in history.iter() {
-                    csv_content.push_str(&format!("{},{},{},{},{},{},{},{}\n",
-                        execution.id,
-                        execution.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                        execution.command,
-                        execution.exit_code.unwrap_or(-1),
-                        execution.duration.as_millis(),
-                        execution.changes_detected,
-                        execution.line_count,
-                        execution.byte_count
-                    ));
-                }
-                tokio::fs::write(filename, csv_content).await?;
-            }
-            _ => return Err(anyhow!("Unsupported export format: {}", format)),
-        }
-        
-        Ok(())
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        Ok(sleep_unless(timeout, &self.stopped))
     }
 }
 
-// Main CLI interface
-pub async fn watch_cli(args: &[String]) -> Result<()> {
-    if args.is_empty() {
-        return Err(anyhow!("watch: usage: watch [OPTIONS] command [args...]"));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
     }
 
-    let mut config = WatchConfig::default();
-    let mut command_args = Vec::new();
-    let mut show_help = false;
-    let mut export_format = None;
-    let mut export_filename = None;
-    let i18n = I18n::new("en-US")?; // Should be configurable
+    #[test]
+    fn parses_options_up_to_the_command() {
+        let parsed = options(&["-dn", "0.5", "ls", "-l", "-n"]).unwrap();
+        assert!(parsed.differences);
+        assert_eq!(parsed.interval, Duration::from_millis(500));
+        assert_eq!(parsed.command, ["ls", "-l", "-n"]);
 
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--help" | "-h" => show_help = true,
-            "-n" | "--interval" => {
-                i += 1;
-                if i < args.len() {
-                    config.interval = args[i].parse()
-                        .context("Invalid interval value")?;
-                } else {
-                    return Err(anyhow!("-n requires an argument"));
-                }
-            }
-            "-d" | "--differences" => config.show_differences = true,
-            "-t" | "--no-title" => config.show_header = false,
-            "-c" | "--color" => config.color_enabled = true,
-            "-b" | "--beep" => config.beep_on_change = true,
-            "-e" | "--errexit" => config.exit_on_error = true,
-            "-g" | "--chgexit" => config.exit_on_change = true,
-            "--precise" => config.precise_timing = true,
-            "--stats" => config.show_statistics = true,
-            "--mouse" => config.mouse_enabled = true,
-            "--no-wrap" => config.line_wrap = false,
-            "--line-numbers" => config.show_line_numbers = true,
-            "--compact" => config.display_mode = DisplayMode::Compact,
-            "--split" => config.display_mode = DisplayMode::Split,
-            "--dashboard" => config.display_mode = DisplayMode::Dashboard,
-            "--minimal" => config.display_mode = DisplayMode::Minimal,
-            "--export" => {
-                i += 1;
-                if i < args.len() {
-                    export_format = Some(args[i].clone());
-                } else {
-                    return Err(anyhow!("--export requires a format"));
-                }
-            }
-            "--export-file" => {
-                i += 1;
-                if i < args.len() {
-                    export_filename = Some(args[i].clone());
-                } else {
-                    return Err(anyhow!("--export-file requires a filename"));
-                }
-            }
-            arg if arg.starts_with("--") => {
-                return Err(anyhow!("Unknown option: {}", arg));
-            }
-            _ => {
-                command_args.extend_from_slice(&args[i..]);
-                break;
-            }
-        }
-        i += 1;
-    }
+        let parsed = options(&["-n0", "--no-title", "date"]).unwrap();
+        assert_eq!(parsed.interval, Duration::from_secs_f64(MIN_INTERVAL));
+        assert!(!parsed.title);
 
-    if show_help {
-        print_watch_help(&i18n);
-        return Ok(());
+        assert_eq!(options(&["-d"]).unwrap_err(), "no command given");
+        assert_eq!(
+            options(&["-n", "soon", "date"]).unwrap_err(),
+            "invalid interval 'soon'"
+        );
     }
 
-    if command_args.is_empty() {
-        return Err(anyhow!("No command specified"));
+    #[test]
+    fn highlights_changed_characters() {
+        assert_eq!(
+            highlight("load 0.51\nok", "load 0.73\nok\nnew"),
+            "load 0.\x1b[7m73\x1b[27m\nok\n\x1b[7mnew\x1b[27m"
+        );
+        assert_eq!(highlight("same", "same"), "same");
     }
 
-    let command = command_args[0].clone();
-    let args = command_args[1..].to_vec();
-
-    let mut watch_manager = WatchManager::new(command, args, config, i18n)?;
-    
-    // Handle export request
-    if let (Some(format), Some(filename)) = (export_format, export_filename) {
-        // Run a few iterations first
-        for _ in 0..10 {
-            let execution_id = watch_manager.execution_counter.fetch_add(1, Ordering::Relaxed);
-            if let Ok(execution) = watch_manager.execute_command(execution_id).await {
-                watch_manager.process_execution_result(execution).await?;
-            }
-            sleep(Duration::from_secs_f64(watch_manager.config.interval)).await;
-        }
-        
-        watch_manager.export_history(&format, &filename).await?;
-        println!("History exported to {} in {} format", filename, format);
-        return Ok(());
+    #[test]
+    fn header_spans_the_width() {
+        let line = header(
+            Duration::from_secs(2),
+            "df -h",
+            "box",
+            "Mon Jan  1 00:00:00 2024",
+            50,
+        );
+        assert_eq!(line, "Every 2.0s: df -h    box: Mon Jan  1 00:00:00 2024");
+        assert_eq!(line.chars().count(), 50);
     }
-
-    // Run the watch manager
-    watch_manager.run().await
 }
-
-fn print_watch_help(i18n: &I18n) {
-    println!("{}", i18n.get("watch.help.title", None));
-    println!();
-    println!("{}", i18n.get("watch.help.usage", None));
-    println!("    watch [OPTIONS] command [args...]");
-    println!();
-    println!("{}", i18n.get("watch.help.options", None));
-    println!("    -h, --help              Show this help message");
-    println!("    -n, --interval SEC      Set update interval in seconds (default: 2.0)");
-    println!("    -d, --differences       Highlight differences between updates");
-    println!("    -t, --no-title          Hide header information");
-    println!("    -c, --color             Enable ANSI color interpretation");
-    println!("    -b, --beep              Beep when command output changes");
-    println!("    -e, --errexit           Exit on command error");
-    println!("    -g, --chgexit           Exit when output changes");
-    println!("    --precise               Use precise timing intervals");
-    println!("    --stats                 Show execution statistics");
-    println!("    --mouse                 Enable mouse support");
-    println!("    --no-wrap               Disable line wrapping");
-    println!("    --line-numbers          Show line numbers");
-    println!("    --compact               Use compact display mode");
-    println!("    --split                 Use split-screen display mode");
-    println!("    --dashboard             Use dashboard display mode");
-    println!("    --minimal               Use minimal display mode");
-    println!("    --export FORMAT         Export history (json, csv)");
-    println!("    --export-file FILE      Export filename");
-    println!();
-    println!("{}", i18n.get("watch.help.keyboard", None));
-    println!("    q, Q                    Quit");
-    println!("    p, P                    Pause/resume");
-    println!("    r, R                    Force refresh");
-    println!("    d, D                    Toggle differences");
-    println!("    s, S                    Toggle statistics");
-    println!("    Up/Down Arrow           Scroll up/down");
-    println!("    Page Up/Down            Scroll page up/down");
-    println!("    Home                    Go to top");
-    println!("    Ctrl+C                  Quit");
-    println!();
-    println!("{}", i18n.get("watch.help.examples", None));
-    println!("    watch ls -la                    # Watch directory listing");
-    println!("    watch -n 0.5 date              # Update every 0.5 seconds");
-    println!("    watch -d 'ps aux | grep nginx' # Watch with differences");
-    println!("    watch --dashboard htop          # Dashboard view");
-    println!("    watch --export json --export-file history.json uptime");
-} 
-
@@ -1,403 +1,1729 @@
-//! `wget` builtin - Non-interactive network downloader with advanced features.
+//! `wget` builtin - download files over HTTP
 //!
-//! Delegates to the system `wget` binary when available in `PATH` to preserve the
-//! complete feature set and CLI surface area. When the binary is unavailable
-//! (e.g. minimal containers or Windows without Git for Windows), it falls back
-//! to an enhanced internal implementation that supports common wget operations.
-
-use anyhow::{anyhow, Context, Result};
-use std::fs::File;
-use std::io::{copy, BufWriter, Write};
+//! Syntax:
+//!   wget [OPTIONS] URL...
+//!
+//! Saves each URL, taken as `http://` when it has no scheme, to the file its
+//! path ends in, or `index.html` for a directory. A name already taken gets
+//! `.1`, `.2` and so on added, unless `-c` continues it.
+//!
+//! Options:
+//!   -O, --output-document FILE  write everything to FILE, - for standard
+//!                               output
+//!   -P, --directory-prefix DIR  save files under DIR
+//!   -c, --continue              continue a partly downloaded file where it
+//!                               stops, with a Range request
+//!   -r, --recursive             follow the links of the HTML pages fetched,
+//!                               staying on their host
+//!   -l, --level DEPTH           follow links DEPTH deep, 5 by default and
+//!                               `inf` or 0 for no limit
+//!   -np, --no-parent            never climb above the starting directory
+//!   -nH, --no-host-directories  no host name directory for recursive saves
+//!   -nd, --no-directories       save every file in one directory
+//!   -e, --execute robots=off    ignore robots.txt while recursing
+//!       --limit-rate RATE       download at most RATE bytes a second, with
+//!                               k, m or g for KiB, MiB or GiB
+//!       --segments N            fetch a file in N parallel Range requests;
+//!                               files of 8 MiB or more take 4 without it
+//!   -t, --tries N               try each file N times, 20 by default and
+//!                               `inf` or 0 for no limit
+//!       --waitretry SECS        wait at most SECS between tries, 10 by default
+//!   -T, --timeout SECS          give up on a connection idle for SECS
+//!   -U, --user-agent AGENT      the User-Agent header
+//!       --header 'NAME: VALUE'  add a header
+//!   -q, --quiet                 print nothing
+//!   -nv, --no-verbose           print a line for each file and the errors
+//!   -v, --verbose               print the whole conversation, the default
+//!
+//! Long options take their value after `=` too, as in `--limit-rate=200k`.
+//!
+//! A recursive download saves each page under a directory named after its
+//! host, mirroring the URL's path, and skips what the host's robots.txt
+//! disallows for `*` or for wget. A transfer that breaks off is tried again,
+//! waiting 1 second more each time, and continued with a Range request when
+//! the server allows. Files are split into segments only when the server
+//! takes Range requests; with `--limit-rate` the segments share the rate.
+//!
+//! The log goes to standard error, with a progress bar when that is a
+//! terminal. Proxies come from `http_proxy`, `all_proxy` and `no_proxy`.
+//!
+//! HTTP is spoken by the shell's own client, which has no TLS: `https`
+//! URLs are handed to the system's `wget` when there is one.
+//!
+//! The exit status is wget's: 0 on success, 1 for a generic error, 2 for
+//! bad options, 3 when a file cannot be written, 4 for a network failure
+//! and 8 for an error answer from the server. Of several failures the
+//! lowest status above 1 wins.
+
+use crate::common::http::{self, Body, Proxy, Request, Response, Transport};
+use crate::common::{io_message, seconds, BuiltinContext, BuiltinResult};
+use chrono::Local;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_ui::ProgressBar;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-#[cfg(feature = "net-http")]
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use url::Url;
-use which::which;
 
-#[derive(Debug, Clone)]
-pub struct WgetOptions {
-    url: String,
+/// The User-Agent header without `-U`
+const USER_AGENT: &str = concat!("Wget/", env!("CARGO_PKG_VERSION"), " (nxsh)");
+
+/// How deep `-r` goes without `-l`
+const DEFAULT_LEVEL: u32 = 5;
+
+/// Tries for each file without `-t`
+const DEFAULT_TRIES: u32 = 20;
+
+/// The longest wait between tries without `--waitretry`
+const DEFAULT_WAITRETRY: Duration = Duration::from_secs(10);
+
+/// Redirects followed for one file
+const MAX_REDIRECTS: u32 = 20;
+
+/// Files this large are split without `--segments`
+const SEGMENT_THRESHOLD: u64 = 8 << 20;
+
+/// Segments a large file is split into without `--segments`
+const DEFAULT_SEGMENTS: usize = 4;
+
+/// How often the progress bar redraws
+const METER_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often a wait checks the shell's time limit
+const TICK: Duration = Duration::from_millis(50);
+
+/// The long options that take a value
+const VALUED: &[&str] = &[
+    "--output-document",
+    "--directory-prefix",
+    "--level",
+    "--execute",
+    "--limit-rate",
+    "--segments",
+    "--tries",
+    "--waitretry",
+    "--timeout",
+    "--user-agent",
+    "--header",
+];
+
+/// The short options and their long names
+const SHORT: &[(char, &str)] = &[
+    ('O', "--output-document"),
+    ('P', "--directory-prefix"),
+    ('l', "--level"),
+    ('e', "--execute"),
+    ('t', "--tries"),
+    ('T', "--timeout"),
+    ('U', "--user-agent"),
+    ('c', "--continue"),
+    ('r', "--recursive"),
+    ('q', "--quiet"),
+    ('v', "--verbose"),
+];
+
+/// The two letter options wget spells with one dash
+const WORDS: &[(&str, &str)] = &[
+    ("-np", "--no-parent"),
+    ("-nH", "--no-host-directories"),
+    ("-nd", "--no-directories"),
+    ("-nv", "--no-verbose"),
+];
+
+/// The `wget` builtin command implementation
+pub struct WgetCommand;
+
+impl Builtin for WgetCommand {
+    fn name(&self) -> &'static str {
+        "wget"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Download files over HTTP"
+    }
+
+    fn description(&self) -> &'static str {
+        "Download files, continuing partial ones, mirroring sites recursively within \
+         robots.txt, limiting the rate and splitting large files into parallel segments."
+    }
+
+    fn usage(&self) -> &'static str {
+        "wget [-crqv] [-nv] [-np] [-O FILE] [-P DIR] [-l DEPTH] [--limit-rate RATE] \
+         [--segments N] [-t TRIES] [-T SECS] URL..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Download files over HTTP. Use 'wget -c URL' to continue a download, \
+         'wget -r -np -l 2 URL' to mirror a directory two links deep or \
+         'wget --limit-rate=100k URL' to save bandwidth."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        // The log is written as it happens on an interactive terminal, so
+        // the progress bar can move; standard error moves out for the run,
+        // leaving the context free to answer the lookups
+        let cwd = ctx.cwd.clone();
+        let interactive = ctx.is_interactive();
+        let mut stderr = std::mem::replace(&mut ctx.stderr, Box::new(io::sink()));
+        let outcome = run(
+            args,
+            Env {
+                cwd: &cwd,
+                live: interactive.then_some(&mut *stderr as &mut dyn Write),
+                var: &|name| ctx.get_var(name),
+                stopped: &|| ctx.is_timed_out(),
+            },
+        );
+        ctx.stderr = stderr;
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run wget for the legacy dispatcher on the process's standard streams
+/// and environment
+pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let cwd = std::env::current_dir()?;
+    let mut stderr = io::stderr();
+    let terminal = stderr.is_terminal();
+    let outcome = run(
+        args,
+        Env {
+            cwd: &cwd,
+            live: terminal.then_some(&mut stderr as &mut dyn Write),
+            var: &|name| std::env::var(name).ok(),
+            stopped: &|| false,
+        },
+    );
+    io::stdout().write_all(&outcome.stdout)?;
+    io::stderr().write_all(&outcome.stderr)?;
+    Ok(outcome.status)
+}
+
+/// What a run reads and writes besides its options
+struct Env<'a> {
+    cwd: &'a Path,
+    /// The terminal to log to as it happens, if any
+    live: Option<&'a mut dyn Write>,
+    var: &'a dyn Fn(&str) -> Option<String>,
+    stopped: &'a dyn Fn() -> bool,
+}
+
+#[derive(Default)]
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Brief,
+    Full,
+}
+
+#[derive(Debug)]
+struct Options {
+    urls: Vec<String>,
     output: Option<String>,
-    verbose: bool,
-    quiet: bool,
-    continue_download: bool,
-    timeout: Option<u64>,
-    tries: Option<u32>,
+    prefix: Option<String>,
+    resume: bool,
+    recursive: bool,
+    /// How deep to recurse, or `None` for no limit
+    level: Option<u32>,
+    no_parent: bool,
+    no_host_directories: bool,
+    no_directories: bool,
+    robots: bool,
+    /// Bytes a second
+    limit_rate: Option<u64>,
+    segments: Option<usize>,
+    /// Tries for each file, or 0 for no limit
+    tries: u32,
+    waitretry: Duration,
+    timeout: Option<Duration>,
     user_agent: Option<String>,
-    header: Vec<String>,
-    use_internal: bool,
+    headers: Vec<String>,
+    verbosity: Verbosity,
 }
 
-impl Default for WgetOptions {
-    fn default() -> Self {
-        Self {
-            url: String::new(),
+/// A bad command line: wget's exit status and message
+type Usage = (i32, String);
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Usage> {
+        let mut options = Options {
+            urls: Vec::new(),
             output: None,
-            verbose: false,
-            quiet: false,
-            continue_download: false,
+            prefix: None,
+            resume: false,
+            recursive: false,
+            level: Some(DEFAULT_LEVEL),
+            no_parent: false,
+            no_host_directories: false,
+            no_directories: false,
+            robots: true,
+            limit_rate: None,
+            segments: None,
+            tries: DEFAULT_TRIES,
+            waitretry: DEFAULT_WAITRETRY,
             timeout: None,
-            tries: Some(1),
             user_agent: None,
-            header: Vec::new(),
-            use_internal: false,
+            headers: Vec::new(),
+            verbosity: Verbosity::Full,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                options.urls.extend(iter.by_ref().cloned());
+            } else if arg.starts_with("--") {
+                let (name, inline) = match arg.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (arg.as_str(), None),
+                };
+                if !VALUED.contains(&name) {
+                    if inline.is_some() {
+                        return Err((2, format!("option '{name}' doesn't allow an argument")));
+                    }
+                    options.set(name, None)?;
+                    continue;
+                }
+                let value = match inline {
+                    Some(value) => value,
+                    None => iter
+                        .next()
+                        .map(String::as_str)
+                        .ok_or_else(|| (2, format!("option '{name}' requires an argument")))?,
+                };
+                options.set(name, Some(value))?;
+            } else if let Some((_, long)) = WORDS.iter().find(|(word, _)| *word == arg.as_str()) {
+                options.set(long, None)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                for (at, flag) in arg[1..].char_indices() {
+                    let long = SHORT
+                        .iter()
+                        .find(|(short, _)| *short == flag)
+                        .map(|(_, long)| *long)
+                        .ok_or_else(|| (2, format!("invalid option -- '{flag}'")))?;
+                    if !VALUED.contains(&long) {
+                        options.set(long, None)?;
+                        continue;
+                    }
+                    let attached = &arg[2 + at..];
+                    let value = match attached {
+                        "" => iter.next().map(String::as_str).ok_or_else(|| {
+                            (2, format!("option requires an argument -- '{flag}'"))
+                        })?,
+                        _ => attached,
+                    };
+                    options.set(long, Some(value))?;
+                    break;
+                }
+            } else {
+                options.urls.push(arg.clone());
+            }
+        }
+        if options.urls.is_empty() {
+            return Err((1, "missing URL".to_string()));
+        }
+        if options.recursive && options.output.is_some() {
+            return Err((2, "-O cannot be combined with -r".to_string()));
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, long: &str, value: Option<&str>) -> Result<(), Usage> {
+        let text = value.unwrap_or_default();
+        let invalid = || (2, format!("{long}: Invalid number '{text}'."));
+        let unlimited = || text.eq_ignore_ascii_case("inf");
+        match long {
+            "--output-document" => self.output = Some(text.to_string()),
+            "--directory-prefix" => self.prefix = Some(text.to_string()),
+            "--level" => {
+                self.level = match text.parse::<u32>() {
+                    Ok(0) => None,
+                    Ok(level) => Some(level),
+                    Err(_) if unlimited() => None,
+                    Err(_) => return Err(invalid()),
+                }
+            }
+            "--execute" => match text.replace(' ', "").to_ascii_lowercase().as_str() {
+                "robots=off" => self.robots = false,
+                "robots=on" => self.robots = true,
+                _ => return Err((2, format!("Invalid --execute command '{text}'"))),
+            },
+            "--limit-rate" => {
+                self.limit_rate =
+                    Some(parse_rate(text).ok_or_else(invalid)?).filter(|&rate| rate > 0)
+            }
+            "--segments" => {
+                self.segments = match text.parse::<usize>() {
+                    Ok(count) if count > 0 => Some(count),
+                    _ => return Err(invalid()),
+                }
+            }
+            "--tries" => {
+                self.tries = match text.parse::<u32>() {
+                    Ok(tries) => tries,
+                    Err(_) if unlimited() => 0,
+                    Err(_) => return Err(invalid()),
+                }
+            }
+            "--waitretry" => self.waitretry = seconds(text).ok_or_else(invalid)?,
+            "--timeout" => {
+                self.timeout = Some(seconds(text).ok_or_else(invalid)?).filter(|t| !t.is_zero())
+            }
+            "--user-agent" => self.user_agent = Some(text.to_string()),
+            "--header" => self.headers.push(text.to_string()),
+            "--continue" => self.resume = true,
+            "--recursive" => self.recursive = true,
+            "--no-parent" => self.no_parent = true,
+            "--no-host-directories" => self.no_host_directories = true,
+            "--no-directories" => self.no_directories = true,
+            "--quiet" => self.verbosity = Verbosity::Quiet,
+            "--no-verbose" => self.verbosity = Verbosity::Brief,
+            "--verbose" => self.verbosity = Verbosity::Full,
+            _ => return Err((2, format!("unrecognized option '{long}'"))),
         }
+        Ok(())
     }
 }
 
-/// Entry point for the `wget` builtin.
-pub fn wget_cli(args: &[String]) -> Result<()> {
-    let options = parse_wget_args(args)?;
+/// A rate like `200k` or `1.5m`, in bytes a second
+fn parse_rate(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (number, unit) = match text.char_indices().last()? {
+        (at, 'k' | 'K') => (&text[..at], 1u64 << 10),
+        (at, 'm' | 'M') => (&text[..at], 1 << 20),
+        (at, 'g' | 'G') => (&text[..at], 1 << 30),
+        _ => (text, 1),
+    };
+    match number.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Some((rate * unit as f64) as u64),
+        _ => None,
+    }
+}
+
+fn run(args: &[String], env: Env) -> Outcome {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err((status, message)) => {
+            return Outcome {
+                stderr: format!("wget: {message}\nUsage: wget [OPTION]... [URL]...\n").into_bytes(),
+                status,
+                ..Outcome::default()
+            }
+        }
+    };
+
+    let mut urls = Vec::new();
+    let mut rejected = String::new();
+    for text in &options.urls {
+        let full = if text.contains("://") {
+            text.clone()
+        } else {
+            format!("http://{text}")
+        };
+        match Url::parse(&full) {
+            Ok(url) if url.scheme() == "http" && url.host_str().is_some() => urls.push(url),
+            Ok(url) if url.scheme() == "https" => {
+                if which::which("wget").is_ok() {
+                    return external(args, env.cwd);
+                }
+                rejected.push_str(&format!(
+                    "{text}: HTTPS is not supported: the shell has no TLS and there is no \
+                     system wget to hand it to.\n"
+                ));
+            }
+            Ok(url) => {
+                rejected.push_str(&format!("{text}: Unsupported scheme '{}'.\n", url.scheme()))
+            }
+            Err(e) => rejected.push_str(&format!("{text}: Invalid URL: {e}.\n")),
+        }
+    }
 
-    // Prefer the full-featured system implementation when present (unless forced internal).
-    if !options.use_internal {
-        if let Ok(result) = try_external_wget(args) {
-            return result;
+    let mut wget = Wget {
+        options: &options,
+        cwd: env.cwd,
+        var: env.var,
+        stopped: env.stopped,
+        log: Log {
+            live: env.live.map(|live| live as &mut dyn Write),
+            buffer: Vec::new(),
+        },
+        stdout: Vec::new(),
+        limiter: options
+            .limit_rate
+            .map(|rate| Arc::new(Mutex::new(Limiter::new(rate)))),
+        status: 0,
+        files: 0,
+        bytes: 0,
+        output_used: false,
+    };
+    if !rejected.is_empty() {
+        wget.note(&rejected);
+        wget.fail(1);
+    }
+    let started = Instant::now();
+    if options.recursive {
+        wget.crawl(&urls);
+    } else {
+        for url in &urls {
+            wget.single(url);
         }
+    }
+    if options.recursive || urls.len() > 1 {
+        let elapsed = started.elapsed();
+        let summary = format!(
+            "FINISHED --{}--\nTotal wall clock time: {:.1}s\nDownloaded: {} files, {} in {:.1}s ({})\n",
+            now(),
+            elapsed.as_secs_f64(),
+            wget.files,
+            human(wget.bytes),
+            elapsed.as_secs_f64(),
+            speed(wget.bytes, elapsed)
+        );
+        wget.note(&summary);
+    }
+    Outcome {
+        stdout: wget.stdout,
+        stderr: wget.log.buffer,
+        status: wget.status,
+    }
+}
+
+/// Standard error, written as it happens on a terminal and kept otherwise
+struct Log<'a> {
+    live: Option<&'a mut dyn Write>,
+    buffer: Vec<u8>,
+}
 
-        if options.verbose {
-            println!("wget: external binary not found, using internal implementation");
+impl Log<'_> {
+    fn write(&mut self, text: &str) {
+        match self.live.as_mut() {
+            Some(out) => {
+                // Best effort: a terminal that cannot be written to just
+                // goes quiet
+                let _ = out.write_all(text.as_bytes());
+                let _ = out.flush();
+            }
+            None => self.buffer.extend_from_slice(text.as_bytes()),
         }
     }
+}
 
-    // Use internal implementation
-    run_internal_wget(&options)
+/// Where a file goes
+enum Target {
+    Stdout,
+    File(PathBuf),
 }
 
-fn try_external_wget(args: &[String]) -> Result<Result<()>> {
-    if let Ok(path) = which("wget") {
-        let status = Command::new(path)
-            .args(args)
-            .status()
-            .map_err(|e| anyhow!("wget: failed to launch backend: {e}"))?;
-        std::process::exit(status.code().unwrap_or(1));
+impl Target {
+    /// The name the log shows, relative to `cwd` when the file is below it
+    fn name(&self, cwd: &Path) -> String {
+        match self {
+            Target::Stdout => "-".to_string(),
+            Target::File(path) => path.strip_prefix(cwd).unwrap_or(path).display().to_string(),
+        }
     }
+}
 
-    Err(anyhow!("wget: backend not found"))
+/// A file downloaded
+struct Fetched {
+    /// The URL it came from, after redirects
+    url: Url,
+    html: bool,
 }
 
-fn parse_wget_args(args: &[String]) -> Result<WgetOptions> {
-    let mut options = WgetOptions::default();
-    let mut i = 0;
+/// The state of a run
+struct Wget<'a> {
+    options: &'a Options,
+    cwd: &'a Path,
+    var: &'a dyn Fn(&str) -> Option<String>,
+    stopped: &'a dyn Fn() -> bool,
+    log: Log<'a>,
+    stdout: Vec<u8>,
+    limiter: Option<Arc<Mutex<Limiter>>>,
+    status: i32,
+    files: u32,
+    bytes: u64,
+    /// Whether the `-O` file was started, so the next URL adds to it
+    output_used: bool,
+}
+
+impl Wget<'_> {
+    /// Log what every level but `-q` prints
+    fn note(&mut self, text: &str) {
+        if self.options.verbosity != Verbosity::Quiet {
+            self.log.write(text);
+        }
+    }
+
+    /// Log what only the full log prints
+    fn chat(&mut self, text: &str) {
+        if self.options.verbosity == Verbosity::Full {
+            self.log.write(text);
+        }
+    }
+
+    /// Record a failure, keeping the most telling status
+    fn fail(&mut self, status: i32) {
+        self.status = match (self.status, status) {
+            (0, new) | (1, new) => new,
+            (old, 1) => old,
+            (old, new) => old.min(new),
+        };
+    }
 
-    while i < args.len() {
-        match args[i].as_str() {
-            "-h" | "--help" => {
-                print_wget_help();
-                std::process::exit(0);
+    fn single(&mut self, url: &Url) {
+        let options = self.options;
+        let (target, base, offset) = match options.output.as_deref() {
+            Some("-") => (Target::Stdout, 0, 0),
+            Some(path) => {
+                let path = self.cwd.join(path);
+                let size = file_size(&path);
+                let target = Target::File(path);
+                match (self.output_used, options.resume) {
+                    (true, _) => (target, size, 0),
+                    (false, true) => (target, 0, size),
+                    (false, false) => (target, 0, 0),
+                }
             }
-            "-v" | "--verbose" => {
-                options.verbose = true;
+            None => {
+                let dir = self.directory();
+                if let Err(e) = fs::create_dir_all(&dir) {
+                    let message = format!("{}: {}\n", dir.display(), io_message(&e));
+                    self.note(&message);
+                    return self.fail(3);
+                }
+                let path = dir.join(remote_name(url));
+                if options.resume {
+                    let size = file_size(&path);
+                    (Target::File(path), 0, size)
+                } else {
+                    (Target::File(unused(path)), 0, 0)
+                }
             }
-            "-q" | "--quiet" => {
-                options.quiet = true;
+        };
+        self.output_used = true;
+        if let Err(status) = self.fetch(url, &target, base, offset) {
+            self.fail(status);
+        }
+    }
+
+    /// The `-P` directory
+    fn directory(&self) -> PathBuf {
+        match &self.options.prefix {
+            Some(prefix) => self.cwd.join(prefix),
+            None => self.cwd.to_path_buf(),
+        }
+    }
+
+    /// Download the pages `starts` link to, breadth first
+    fn crawl(&mut self, starts: &[Url]) {
+        let mut queue = VecDeque::new();
+        let mut seen = HashSet::new();
+        for start in starts {
+            if seen.insert(start.as_str().to_string()) {
+                queue.push_back((start.clone(), 0, start.clone()));
             }
-            "--internal" => {
-                options.use_internal = true;
+        }
+        let mut robots: HashMap<String, Robots> = HashMap::new();
+        while let Some((url, depth, start)) = queue.pop_front() {
+            if (self.stopped)() {
+                return self.fail(4);
             }
-            "-O" | "--output-document" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("wget: -O requires a filename"));
+            if depth > 0 && self.options.robots {
+                let origin = url[..url::Position::BeforePath].to_string();
+                if !robots.contains_key(&origin) {
+                    let rules = self.robots(&url);
+                    robots.insert(origin.clone(), rules);
+                }
+                let path = &url[url::Position::BeforePath..url::Position::AfterQuery];
+                if robots.get(&origin).is_some_and(|rules| !rules.allows(path)) {
+                    continue;
                 }
-                options.output = Some(args[i].clone());
-            }
-            "-c" | "--continue" => {
-                options.continue_download = true;
             }
-            "-T" | "--timeout" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("wget: -T requires a timeout value"));
+            let path = self.mirror_path(&url);
+            if let Some(dir) = path.parent() {
+                if let Err(e) = fs::create_dir_all(dir) {
+                    let message = format!("{}: {}\n", dir.display(), io_message(&e));
+                    self.note(&message);
+                    self.fail(3);
+                    continue;
                 }
-                options.timeout = Some(
-                    args[i]
-                        .parse()
-                        .map_err(|_| anyhow!("wget: invalid timeout value: {}", args[i]))?,
-                );
             }
-            "-t" | "--tries" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("wget: -t requires a retry count"));
+            let offset = if self.options.resume {
+                file_size(&path)
+            } else {
+                0
+            };
+            let fetched = match self.fetch(&url, &Target::File(path.clone()), 0, offset) {
+                Ok(fetched) => fetched,
+                Err(status) => {
+                    self.fail(status);
+                    continue;
                 }
-                options.tries = Some(
-                    args[i]
-                        .parse()
-                        .map_err(|_| anyhow!("wget: invalid tries value: {}", args[i]))?,
-                );
+            };
+            let deeper = self.options.level.is_none_or(|level| depth < level);
+            if !fetched.html || !deeper {
+                continue;
             }
-            "-U" | "--user-agent" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("wget: -U requires a user agent"));
+            let Ok(page) = fs::read(&path) else {
+                continue;
+            };
+            for link in links(&String::from_utf8_lossy(&page)) {
+                let Ok(mut next) = fetched.url.join(&link) else {
+                    continue;
+                };
+                next.set_fragment(None);
+                let same_host = next.scheme() == "http"
+                    && next.host_str() == start.host_str()
+                    && next.port_or_known_default() == start.port_or_known_default();
+                if !same_host || (self.options.no_parent && !below(&start, &next)) {
+                    continue;
+                }
+                if seen.insert(next.as_str().to_string()) {
+                    queue.push_back((next, depth + 1, start.clone()));
                 }
-                options.user_agent = Some(args[i].clone());
             }
-            "--header" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err(anyhow!("wget: --header requires a header"));
+        }
+    }
+
+    /// The robots.txt rules of `url`'s host, allowing everything when it
+    /// has none
+    fn robots(&mut self, url: &Url) -> Robots {
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return Robots::default();
+        };
+        let Ok(transport) = self.transport(&robots_url) else {
+            return Robots::default();
+        };
+        match http::exchange(&self.request(&robots_url, 0), &transport) {
+            Ok(response) if response.status == 200 => {
+                Robots::parse(&String::from_utf8_lossy(&response.body))
+            }
+            _ => Robots::default(),
+        }
+    }
+
+    /// Where `-r` saves `url`
+    fn mirror_path(&self, url: &Url) -> PathBuf {
+        let mut path = self.directory();
+        let mut segments: Vec<String> = url
+            .path_segments()
+            .map(|segments| segments.map(local_name).collect())
+            .unwrap_or_default();
+        let file = segments.pop().filter(|name| !name.is_empty());
+        let file = file.unwrap_or_else(|| "index.html".to_string());
+        let file = match url.query() {
+            Some(query) => format!("{file}?{}", local_name(query)),
+            None => file,
+        };
+        if !self.options.no_directories {
+            if !self.options.no_host_directories {
+                let host = url.host_str().unwrap_or_default();
+                match url.port() {
+                    Some(port) => path.push(format!("{host}:{port}")),
+                    None => path.push(host),
                 }
-                options.header.push(args[i].clone());
             }
-            arg if !arg.starts_with('-') => {
-                if options.url.is_empty() {
-                    options.url = arg.to_string();
-                } else {
-                    return Err(anyhow!("wget: too many URLs specified"));
+            for dir in segments {
+                if !dir.is_empty() && dir != "." && dir != ".." {
+                    path.push(dir);
                 }
             }
-            _ => {
-                return Err(anyhow!("wget: unknown option: {}", args[i]));
+        }
+        path.push(file);
+        path
+    }
+
+    fn request(&self, url: &Url, offset: u64) -> Request {
+        let mut headers = vec![
+            (
+                "User-Agent".to_string(),
+                self.options
+                    .user_agent
+                    .clone()
+                    .unwrap_or_else(|| USER_AGENT.to_string()),
+            ),
+            ("Accept".to_string(), "*/*".to_string()),
+            ("Accept-Encoding".to_string(), "identity".to_string()),
+        ];
+        for header in &self.options.headers {
+            if let Some((name, value)) = header.split_once(':') {
+                let name = name.trim();
+                headers.retain(|(known, _)| !known.eq_ignore_ascii_case(name));
+                headers.push((name.to_string(), value.trim().to_string()));
             }
         }
-        i += 1;
+        if offset > 0 {
+            headers.push(("Range".to_string(), format!("bytes={offset}-")));
+        }
+        Request {
+            method: "GET".to_string(),
+            url: url.clone(),
+            headers,
+            body: Vec::new(),
+            http10: false,
+        }
     }
 
-    if options.url.is_empty() {
-        return Err(anyhow!("wget: no URL specified"));
+    fn transport(&self, url: &Url) -> Result<Transport, String> {
+        Ok(Transport {
+            proxy: Proxy::from_env(url, None, self.var)?,
+            connect_timeout: self.options.timeout,
+            deadline: None,
+            idle_timeout: self.options.timeout,
+        })
     }
 
-    Ok(options)
-}
+    /// Download `url` to `target`, whose first `base` bytes belong to
+    /// earlier URLs and whose next `offset` bytes are already this one's,
+    /// returning the exit status of a failure
+    fn fetch(
+        &mut self,
+        original: &Url,
+        target: &Target,
+        base: u64,
+        mut offset: u64,
+    ) -> Result<Fetched, i32> {
+        let mut url = original.clone();
+        let mut tries = 0;
+        let mut redirects = 0;
+        self.chat(&format!("--{}--  {url}\n", now()));
+        loop {
+            if (self.stopped)() {
+                return Err(4);
+            }
+            tries += 1;
+            let transport = match self.transport(&url) {
+                Ok(transport) => transport,
+                Err(message) => {
+                    self.note(&format!("wget: {message}\n"));
+                    return Err(4);
+                }
+            };
+            let request = self.request(&url, offset);
+            let (response, mut body) = match http::open(&request, &transport) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    let hop = endpoint(&url, &transport);
+                    let message = match &e {
+                        http::Error::Resolve(host) | http::Error::ResolveProxy(host) => format!(
+                            "Resolving {host}... failed: Name or service not known.\n\
+                             wget: unable to resolve host address '{host}'\n"
+                        ),
+                        http::Error::Connect { reason, .. } => {
+                            format!("Connecting to {hop}... failed: {reason}.\n")
+                        }
+                        http::Error::Timeout(_) => {
+                            format!("Connecting to {hop}... failed: Connection timed out.\n")
+                        }
+                        e => format!(
+                            "HTTP request sent, awaiting response... Read error ({e}) in headers.\n"
+                        ),
+                    };
+                    self.note(&message);
+                    let transient = !matches!(
+                        e,
+                        http::Error::Resolve(_)
+                            | http::Error::ResolveProxy(_)
+                            | http::Error::Connect { .. }
+                    );
+                    if transient && self.again(tries, &url) {
+                        continue;
+                    }
+                    return Err(4);
+                }
+            };
+            let hop = endpoint(&url, &transport);
+            self.chat(&format!(
+                "Connecting to {hop}... connected.\nHTTP request sent, awaiting response... {} {}\n",
+                response.status, response.reason
+            ));
 
-fn print_wget_help() {
-    println!("Usage: wget [options] URL");
-    println!();
-    println!("Options:");
-    println!("  -h, --help                Show this help message");
-    println!("  -v, --verbose             Enable verbose output");
-    println!("  -q, --quiet               Turn off output");
-    println!("  -O, --output-document=F   Write documents to FILE");
-    println!("  -c, --continue            Resume getting a partially-downloaded file");
-    println!("  -T, --timeout=SECONDS     Set the network timeout");
-    println!("  -t, --tries=NUMBER        Set number of retries to NUMBER (0 unlimits)");
-    println!("  -U, --user-agent=AGENT    Identify as AGENT instead of wget");
-    println!("  --header=STRING           Insert STRING among the headers sent");
-    println!("  --internal                Force use of internal implementation");
-    println!();
-    println!("Examples:");
-    println!("  wget https://example.com/file.txt");
-    println!("  wget -O myfile.txt https://example.com/file.txt");
-    println!("  wget -v -c https://example.com/largefile.zip");
-    println!("  wget --header='Authorization: Bearer token' https://api.example.com/data");
-}
+            if matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+                if let Some(location) = response.header("location") {
+                    let location = location.to_string();
+                    redirects += 1;
+                    if redirects > MAX_REDIRECTS {
+                        self.note(&format!("{MAX_REDIRECTS} redirections exceeded.\n"));
+                        return Err(8);
+                    }
+                    let next = match url.join(&location) {
+                        Ok(next) if next.scheme() == "http" => next,
+                        _ => {
+                            self.note(&format!(
+                                "Location: {location}\n{location}: Unsupported scheme.\n"
+                            ));
+                            return Err(1);
+                        }
+                    };
+                    self.chat(&format!(
+                        "Location: {location} [following]\n--{}--  {next}\n",
+                        now()
+                    ));
+                    url = next;
+                    tries = 0;
+                    continue;
+                }
+            }
+            if response.status == 416 && offset > 0 {
+                self.chat("\n    The file is already fully retrieved; nothing to do.\n\n");
+                return Ok(Fetched {
+                    html: is_html(&url, &response),
+                    url,
+                });
+            }
+            if response.status >= 400 {
+                let message = format!(
+                    "{} ERROR {}: {}.\n",
+                    now(),
+                    response.status,
+                    response.reason
+                );
+                self.note(&message);
+                self.chat("\n");
+                return Err(8);
+            }
 
-#[cfg(feature = "net-http")]
-fn run_internal_wget(options: &WgetOptions) -> Result<()> {
-    let parsed_url = Url::parse(&options.url).context("wget: invalid URL")?;
+            // A server that ignores the Range request sends it all again
+            let resumed = offset > 0 && response.status == 206;
+            let mut skip = 0;
+            if offset > 0 && !resumed {
+                match target {
+                    Target::Stdout => skip = offset,
+                    Target::File(_) => offset = 0,
+                }
+            }
+            let left = body.remaining();
+            let total = left.map(|left| left + if resumed { offset } else { 0 });
+            let kind = response.header("content-type").unwrap_or("text/plain");
+            let length = match (left, resumed) {
+                (Some(left), true) => format!(
+                    "Length: {} ({}), {left} ({}) remaining [{kind}]\n",
+                    left + offset,
+                    human(left + offset),
+                    human(left)
+                ),
+                (Some(left), false) => format!("Length: {left} ({}) [{kind}]\n", human(left)),
+                (None, _) => format!("Length: unspecified [{kind}]\n"),
+            };
+            let saving = match target {
+                Target::Stdout => "Saving to: 'STDOUT'\n\n".to_string(),
+                Target::File(_) => format!("Saving to: '{}'\n\n", target.name(self.cwd)),
+            };
+            self.chat(&format!("{length}{saving}"));
 
-    // Determine output filename
-    let output_path = if let Some(output) = &options.output {
-        if output == "-" {
-            // Write to stdout
-            download_to_stdout(options)?;
-            return Ok(());
-        }
-        PathBuf::from(output)
-    } else {
-        // Use filename from URL
-        let default_name = parsed_url
-            .path_segments()
-            .and_then(|mut segments| segments.next_back())
-            .filter(|s| !s.is_empty())
-            .unwrap_or("index.html");
-        PathBuf::from(default_name)
-    };
+            let started = Instant::now();
+            let ranged = response
+                .header("accept-ranges")
+                .is_some_and(|unit| unit.eq_ignore_ascii_case("bytes"));
+            let count = match (self.options.segments, left) {
+                (Some(count), Some(size)) => count.min(size.max(1) as usize),
+                (None, Some(size)) if size >= SEGMENT_THRESHOLD => DEFAULT_SEGMENTS,
+                _ => 1,
+            };
+            let got = match target {
+                Target::File(path) if ranged && count > 1 && offset == 0 && !resumed => {
+                    drop(body);
+                    let size = left.unwrap_or_default();
+                    self.segmented(&request, &transport, path, base, size, count)?
+                }
+                _ => {
+                    let mut sink = match self.sink(target, base, if resumed { offset } else { 0 }) {
+                        Ok(sink) => sink,
+                        Err(e) => {
+                            let message =
+                                format!("{}: {}\n", target.name(self.cwd), io_message(&e));
+                            self.note(&message);
+                            return Err(3);
+                        }
+                    };
+                    let done = offset;
+                    match self.stream(&mut body, &mut sink, target, skip, done, total) {
+                        Ok(got) => got,
+                        Err(Broken::Write(e)) => {
+                            let message = format!(
+                                "Cannot write to '{}' ({}).\n",
+                                target.name(self.cwd),
+                                io_message(&e)
+                            );
+                            self.note(&message);
+                            return Err(3);
+                        }
+                        Err(Broken::Read(got, e)) => {
+                            offset = done + got;
+                            let total = total.map_or(String::new(), |total| format!("/{total}"));
+                            self.note(&format!("Read error at byte {offset}{total} ({e}). "));
+                            if self.again(tries, &url) {
+                                continue;
+                            }
+                            return Err(4);
+                        }
+                        Err(Broken::Stopped) => return Err(4),
+                    }
+                }
+            };
 
-    if !options.quiet {
-        println!(
-            "--{}-- {}",
-            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
-            options.url
-        );
-        println!(
-            "Resolving {}...",
-            parsed_url.host_str().unwrap_or("unknown")
-        );
+            let size = offset + got;
+            let of = total.map_or(String::new(), |total| format!("/{total}"));
+            let saved = match target {
+                Target::Stdout => "written to stdout".to_string(),
+                Target::File(_) => format!("'{}' saved", target.name(self.cwd)),
+            };
+            self.chat(&format!(
+                "{} ({}) - {saved} [{size}{of}]\n\n",
+                now(),
+                speed(got, started.elapsed())
+            ));
+            if self.options.verbosity == Verbosity::Brief {
+                let message = format!(
+                    "{} URL:{url} [{size}{of}] -> \"{}\" [1]\n",
+                    now(),
+                    target.name(self.cwd)
+                );
+                self.log.write(&message);
+            }
+            self.files += 1;
+            self.bytes += got;
+            return Ok(Fetched {
+                html: is_html(&url, &response),
+                url,
+            });
+        }
     }
 
-    let mut attempt = 0;
-    let max_tries = options.tries.unwrap_or(1);
+    /// After failed try `tries`, wait and say so when there is another,
+    /// returning whether there is
+    fn again(&mut self, tries: u32, url: &Url) -> bool {
+        let limit = self.options.tries;
+        if limit != 0 && tries >= limit {
+            self.note("Giving up.\n\n");
+            return false;
+        }
+        self.note("Retrying.\n\n");
+        let pause = Duration::from_secs(u64::from(tries)).min(self.options.waitretry);
+        if sleep(pause, self.stopped) {
+            return false;
+        }
+        self.chat(&format!("--{}--  (try:{:2})  {url}\n", now(), tries + 1));
+        true
+    }
 
-    loop {
-        attempt += 1;
+    /// Open where the body goes: the `-O` file after `base` bytes, or a
+    /// file continued after `offset`, or standard output
+    fn sink(&self, target: &Target, base: u64, offset: u64) -> io::Result<Sink> {
+        match target {
+            Target::Stdout => Ok(Sink::Stdout),
+            Target::File(path) => {
+                let mut file = OpenOptions::new().write(true).create(true).open(path)?;
+                file.set_len(base + offset)?;
+                file.seek(SeekFrom::End(0))?;
+                Ok(Sink::File(file))
+            }
+        }
+    }
 
-        match download_file(options, &output_path) {
-            Ok(()) => {
-                if !options.quiet {
-                    println!("'{}' saved", output_path.display());
-                }
-                return Ok(());
+    /// Copy the body to the sink at the rate allowed, drawing progress,
+    /// returning the bytes written
+    fn stream(
+        &mut self,
+        body: &mut Body,
+        sink: &mut Sink,
+        target: &Target,
+        mut skip: u64,
+        done: u64,
+        total: Option<u64>,
+    ) -> Result<u64, Broken> {
+        let mut meter = self.meter(target, total, done);
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut got = 0u64;
+        let result = loop {
+            if (self.stopped)() {
+                break Err(Broken::Stopped);
             }
-            Err(e) => {
-                if attempt >= max_tries {
-                    return Err(e);
+            let n = match body.read(&mut buffer) {
+                Ok(0) => break Ok(got),
+                Ok(n) => n,
+                Err(e) => break Err(Broken::Read(got, body.fail(e))),
+            };
+            let mut data = &buffer[..n];
+            if skip > 0 {
+                let skipped = skip.min(n as u64) as usize;
+                skip -= skipped as u64;
+                data = &data[skipped..];
+            }
+            let written = match sink {
+                Sink::Stdout => {
+                    self.stdout.extend_from_slice(data);
+                    Ok(())
                 }
+                Sink::File(file) => file.write_all(data),
+            };
+            if let Err(e) = written {
+                break Err(Broken::Write(e));
+            }
+            got += data.len() as u64;
+            if let Some(limiter) = &self.limiter {
+                Limiter::pace(limiter, data.len() as u64);
+            }
+            if let Some(meter) = meter.as_mut() {
+                meter.update(&mut self.log, done + got, false);
+            }
+        };
+        if let Some(meter) = meter.as_mut() {
+            meter.finish(&mut self.log, done + got);
+        }
+        result
+    }
 
-                if !options.quiet {
-                    println!("wget: retrying... (attempt {}/{})", attempt + 1, max_tries);
-                }
+    /// A progress bar, on a terminal with the full log
+    fn meter(&self, target: &Target, total: Option<u64>, done: u64) -> Option<Meter> {
+        if self.log.live.is_none() || self.options.verbosity != Verbosity::Full {
+            return None;
+        }
+        let name = match target {
+            Target::Stdout => "-".to_string(),
+            Target::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        Some(Meter {
+            bar: total.filter(|&total| total > 0).map(ProgressBar::new),
+            name,
+            from: done,
+            started: Instant::now(),
+            last: None,
+            drawn: false,
+        })
+    }
 
-                // Simple retry delay
-                std::thread::sleep(std::time::Duration::from_secs(1));
+    /// Download `size` bytes into `path` after `base` in `count` parallel
+    /// Range requests, returning the bytes written
+    fn segmented(
+        &mut self,
+        request: &Request,
+        transport: &Transport,
+        path: &Path,
+        base: u64,
+        size: u64,
+        count: usize,
+    ) -> Result<u64, i32> {
+        let prepared = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(path)
+            .and_then(|file| file.set_len(base + size));
+        if let Err(e) = prepared {
+            let message = format!("{}: {}\n", path.display(), io_message(&e));
+            self.note(&message);
+            return Err(3);
+        }
+        self.chat(&format!("Fetching in {count} segments.\n"));
+        let part = size.div_ceil(count as u64);
+        let ranges: Vec<(u64, u64)> = (0..count as u64)
+            .map(|index| (index * part, ((index + 1) * part).min(size)))
+            .filter(|(start, end)| start < end)
+            .collect();
+
+        let done = AtomicU64::new(0);
+        let cancel = AtomicBool::new(false);
+        let limiter = self.limiter.clone();
+        let tries = self.options.tries;
+        let stopped = self.stopped;
+        let mut meter = self.meter(&Target::File(path.to_path_buf()), Some(size), 0);
+        let results: Vec<Result<(), String>> = thread::scope(|scope| {
+            let handles: Vec<_> = ranges
+                .iter()
+                .map(|&(start, end)| {
+                    let segment = Segment {
+                        request,
+                        transport,
+                        path,
+                        at: base,
+                        tries,
+                        limiter: limiter.as_deref(),
+                        done: &done,
+                        cancel: &cancel,
+                    };
+                    scope.spawn(move || segment.fetch(start, end))
+                })
+                .collect();
+            while handles.iter().any(|handle| !handle.is_finished()) {
+                if stopped() {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                if let Some(meter) = meter.as_mut() {
+                    meter.update(&mut self.log, done.load(Ordering::Relaxed), false);
+                }
+                thread::sleep(TICK);
             }
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err("segment thread panicked".to_string()))
+                })
+                .collect()
+        });
+        if let Some(meter) = meter.as_mut() {
+            meter.finish(&mut self.log, done.load(Ordering::Relaxed));
         }
+        if let Some(Err(message)) = results.into_iter().find(Result::is_err) {
+            self.note(&format!("Segment failed: {message}.\n"));
+            return Err(4);
+        }
+        Ok(size)
     }
 }
 
-#[cfg(not(feature = "net-http"))]
-fn run_internal_wget(_options: &WgetOptions) -> Result<()> {
-    Err(anyhow!(
-        "wget: internal HTTP disabled (built without 'net-http' feature); install system wget or rebuild with --features net-http"
-    ))
+/// Where a body is written
+enum Sink {
+    Stdout,
+    File(File),
 }
 
-/// Execute function stub
-pub fn execute(
-    _args: &[String],
-    _context: &crate::common::BuiltinContext,
-) -> crate::common::BuiltinResult<i32> {
-    eprintln!("Command not yet implemented");
-    Ok(1)
+/// Why copying a body stopped short
+enum Broken {
+    /// Reading failed after that many bytes
+    Read(u64, http::Error),
+    Write(io::Error),
+    Stopped,
 }
 
-#[cfg(feature = "net-http")]
-fn download_file(options: &WgetOptions, output_path: &Path) -> Result<()> {
-    let mut request = ureq::get(&options.url);
+/// One Range request of a segmented download
+struct Segment<'a> {
+    request: &'a Request,
+    transport: &'a Transport,
+    path: &'a Path,
+    /// Where the file's body starts
+    at: u64,
+    tries: u32,
+    limiter: Option<&'a Mutex<Limiter>>,
+    done: &'a AtomicU64,
+    cancel: &'a AtomicBool,
+}
 
-    // Add headers
-    for header in &options.header {
-        if let Some(colon_pos) = header.find(':') {
-            let name = header[..colon_pos].trim();
-            let value = header[colon_pos + 1..].trim();
-            request = request.set(name, value);
+impl Segment<'_> {
+    /// Fetch bytes `start..end` of the body, trying again from where a
+    /// broken transfer stopped
+    fn fetch(&self, mut start: u64, end: u64) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(self.path)
+            .map_err(|e| io_message(&e))?;
+        let mut attempt = 0;
+        let mut buffer = vec![0u8; 64 * 1024];
+        while start < end {
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err("interrupted".to_string());
+            }
+            attempt += 1;
+            let mut request = self.request.clone();
+            request
+                .headers
+                .push(("Range".to_string(), format!("bytes={start}-{}", end - 1)));
+            let failure = match http::open(&request, self.transport) {
+                Ok((response, _)) if response.status != 206 => {
+                    return Err(format!(
+                        "bytes {start}-{}: {} {}",
+                        end - 1,
+                        response.status,
+                        response.reason
+                    ))
+                }
+                Ok((_, mut body)) => {
+                    file.seek(SeekFrom::Start(self.at + start))
+                        .map_err(|e| io_message(&e))?;
+                    loop {
+                        if self.cancel.load(Ordering::Relaxed) {
+                            return Err("interrupted".to_string());
+                        }
+                        let n = match body.read(&mut buffer) {
+                            Ok(0) => break None,
+                            Ok(n) => n.min((end - start) as usize),
+                            Err(e) => break Some(body.fail(e)),
+                        };
+                        file.write_all(&buffer[..n]).map_err(|e| io_message(&e))?;
+                        start += n as u64;
+                        self.done.fetch_add(n as u64, Ordering::Relaxed);
+                        if let Some(limiter) = self.limiter {
+                            Limiter::pace(limiter, n as u64);
+                        }
+                        if start >= end {
+                            break None;
+                        }
+                    }
+                }
+                Err(e) => Some(e),
+            };
+            match failure {
+                None if start < end => {
+                    return Err(format!("bytes {start}-{}: the reply ended early", end - 1))
+                }
+                None => {}
+                Some(e) if self.tries != 0 && attempt >= self.tries => return Err(e.to_string()),
+                Some(_) => thread::sleep(Duration::from_secs(u64::from(attempt).min(10))),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `--limit-rate`, shared by the segments of a download
+struct Limiter {
+    rate: u64,
+    started: Instant,
+    bytes: u64,
+}
+
+impl Limiter {
+    fn new(rate: u64) -> Self {
+        Limiter {
+            rate,
+            started: Instant::now(),
+            bytes: 0,
         }
     }
 
-    // Add User-Agent if specified
-    if let Some(ua) = &options.user_agent {
-        request = request.set("User-Agent", ua);
+    /// Count `bytes` more and wait until they are due at the rate
+    fn pace(limiter: &Mutex<Limiter>, bytes: u64) {
+        let due = {
+            let Ok(mut limiter) = limiter.lock() else {
+                return;
+            };
+            limiter.bytes += bytes;
+            limiter.started + Duration::from_secs_f64(limiter.bytes as f64 / limiter.rate as f64)
+        };
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
     }
+}
+
+/// The progress line drawn on a terminal
+struct Meter {
+    bar: Option<ProgressBar>,
+    name: String,
+    /// The bytes there were before this transfer, for its speed
+    from: u64,
+    started: Instant,
+    last: Option<Instant>,
+    drawn: bool,
+}
 
-    // Set timeout if specified
-    if let Some(timeout) = options.timeout {
-        request = request.timeout(std::time::Duration::from_secs(timeout));
+impl Meter {
+    fn update(&mut self, log: &mut Log, done: u64, force: bool) {
+        if !force
+            && self
+                .last
+                .is_some_and(|last| last.elapsed() < METER_INTERVAL)
+        {
+            return;
+        }
+        self.last = Some(Instant::now());
+        let rate = speed(done.saturating_sub(self.from), self.started.elapsed());
+        let shown = match self.bar.as_mut() {
+            Some(bar) => {
+                bar.set_position(done);
+                bar.set_message(self.name.clone());
+                bar.render()
+            }
+            None => self.name.clone(),
+        };
+        log.write(&format!("\r{shown}  {:>7}  {rate}\x1b[K", human(done)));
+        self.drawn = true;
     }
 
-    if options.verbose {
-        println!("Connecting to {}...", options.url);
+    fn finish(&mut self, log: &mut Log, done: u64) {
+        self.update(log, done, true);
+        if self.drawn {
+            log.write("\n");
+        }
     }
+}
 
-    let response = request
-        .call()
-        .with_context(|| format!("wget: failed to fetch {}", options.url))?;
+/// The rules robots.txt gives wget
+#[derive(Debug, Default)]
+struct Robots {
+    /// Path prefixes, and whether each is allowed
+    rules: Vec<(String, bool)>,
+}
 
-    if response.status() != 200 {
-        return Err(anyhow!(
-            "wget: server responded with HTTP status {}",
-            response.status()
-        ));
+impl Robots {
+    /// The rules of the groups for wget, or else for `*`
+    fn parse(text: &str) -> Robots {
+        let mut ours = Vec::new();
+        let mut anyone = Vec::new();
+        let mut for_wget = false;
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                key @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    let wget = agents.iter().any(|agent| agent.contains("wget"));
+                    for_wget |= wget;
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (value.to_string(), key == "allow");
+                    if wget {
+                        ours.push(rule);
+                    } else if agents.iter().any(|agent| agent == "*") {
+                        anyone.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Robots {
+            rules: if for_wget { ours } else { anyone },
+        }
     }
 
-    if options.verbose {
-        println!(
-            "HTTP request sent, awaiting response... {} {}",
-            response.status(),
-            response.status_text()
-        );
+    /// Whether `path` may be fetched: the longest matching rule decides,
+    /// and an allowing one wins a tie
+    fn allows(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for (prefix, allow) in &self.rules {
+            if !path.starts_with(prefix.as_str()) {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((length, allowed)) => {
+                    prefix.len() > length || (prefix.len() == length && *allow && !allowed)
+                }
+            };
+            if better {
+                best = Some((prefix.len(), *allow));
+            }
+        }
+        best.is_none_or(|(_, allow)| allow)
+    }
+}
 
-        if let Some(content_length) = response.header("Content-Length") {
-            println!("Length: {content_length} bytes");
+/// The `href` and `src` targets of an HTML page, in their order
+fn links(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let mut found = Vec::new();
+    for attribute in ["href", "src"] {
+        for (at, _) in lower.match_indices(attribute) {
+            if at == 0 || !bytes[at - 1].is_ascii_whitespace() {
+                continue;
+            }
+            let mut i = at + attribute.len();
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if bytes.get(i) != Some(&b'=') {
+                continue;
+            }
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let (start, end) = match bytes.get(i) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let end = lower[i + 1..].find(quote as char).map(|n| i + 1 + n);
+                    (i + 1, end.unwrap_or(bytes.len()))
+                }
+                _ => {
+                    let end = lower[i..]
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .map(|n| i + n);
+                    (i, end.unwrap_or(bytes.len()))
+                }
+            };
+            let link = html[start..end].trim().replace("&amp;", "&");
+            let skipped = ["mailto:", "javascript:", "data:", "#"]
+                .iter()
+                .any(|prefix| link.to_ascii_lowercase().starts_with(prefix));
+            if !link.is_empty() && !skipped {
+                found.push((at, link));
+            }
         }
+    }
+    found.sort_by_key(|(at, _)| *at);
+    found.into_iter().map(|(_, link)| link).collect()
+}
+
+/// Whether `next` is at or below the directory of `start`
+fn below(start: &Url, next: &Url) -> bool {
+    let path = start.path();
+    let directory = &path[..path.rfind('/').map_or(0, |at| at + 1)];
+    next.path().starts_with(directory)
+}
 
-        if let Some(content_type) = response.header("Content-Type") {
-            println!("Content-Type: {content_type}");
+fn is_html(url: &Url, response: &Response) -> bool {
+    match response.header("content-type") {
+        Some(kind) => kind.to_ascii_lowercase().contains("html"),
+        None => {
+            let path = url.path().to_ascii_lowercase();
+            path.ends_with('/') || path.ends_with(".html") || path.ends_with(".htm")
         }
     }
+}
 
-    // Handle file writing with resume support
-    let mut file = if options.continue_download && output_path.exists() {
-        std::fs::OpenOptions::new()
-            .append(true)
-            .open(output_path)
-            .with_context(|| format!("wget: cannot open file {output_path:?}"))?
+/// The host and port a request goes to, for the log
+fn endpoint(url: &Url, transport: &Transport) -> String {
+    match &transport.proxy {
+        Some(proxy) => format!("{}:{}", proxy.host, proxy.port),
+        None => format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or(80)
+        ),
+    }
+}
+
+/// A URL path segment as a file name
+fn local_name(segment: &str) -> String {
+    http::decode(segment).replace(['/', '\\'], "%2F")
+}
+
+/// The file the URL's path ends in
+fn remote_name(url: &Url) -> String {
+    let name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(local_name)
+        .unwrap_or_default();
+    if name.is_empty() || name == "." || name == ".." {
+        "index.html".to_string()
     } else {
-        File::create(output_path)
-            .with_context(|| format!("wget: cannot create file {output_path:?}"))?
-    };
+        name
+    }
+}
 
-    let mut writer = BufWriter::new(&mut file);
-    let mut reader = response.into_reader();
+/// `path`, or the first of `path.1`, `path.2` and so on not yet taken
+fn unused(path: PathBuf) -> PathBuf {
+    let mut candidate = path.clone();
+    let mut n = 0;
+    while candidate.exists() {
+        n += 1;
+        let mut name = path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        candidate = PathBuf::from(name);
+    }
+    candidate
+}
 
-    copy(&mut reader, &mut writer).context("wget: failed while writing to file")?;
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
 
-    writer.flush().context("wget: failed to flush file")?;
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
 
-    Ok(())
+/// A size like wget's `1.2K` or `34M`
+fn human(bytes: u64) -> String {
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64;
+    let mut unit = ' ';
+    for next in ['K', 'M', 'G', 'T'] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    if value < 10.0 {
+        format!("{value:.1}{unit}")
+    } else {
+        format!("{value:.0}{unit}")
+    }
 }
 
-#[cfg(feature = "net-http")]
-fn download_to_stdout(options: &WgetOptions) -> Result<()> {
-    let mut request = ureq::get(&options.url);
+/// A transfer speed like wget's `1.21 MB/s`
+fn speed(bytes: u64, elapsed: Duration) -> String {
+    let mut rate = bytes as f64 / elapsed.as_secs_f64().max(0.001);
+    let mut unit = "B/s";
+    for next in ["KB/s", "MB/s", "GB/s"] {
+        if rate < 1024.0 {
+            break;
+        }
+        rate /= 1024.0;
+        unit = next;
+    }
+    let digits = if rate < 10.0 {
+        2
+    } else if rate < 100.0 {
+        1
+    } else {
+        0
+    };
+    format!("{rate:.digits$} {unit}")
+}
 
-    // Add headers
-    for header in &options.header {
-        if let Some(colon_pos) = header.find(':') {
-            let name = header[..colon_pos].trim();
-            let value = header[colon_pos + 1..].trim();
-            request = request.set(name, value);
+/// Wait for `pause`, returning true when the run was stopped meanwhile
+fn sleep(pause: Duration, stopped: &dyn Fn() -> bool) -> bool {
+    let until = Instant::now() + pause;
+    loop {
+        if stopped() {
+            return true;
         }
+        let now = Instant::now();
+        if now >= until {
+            return false;
+        }
+        thread::sleep(TICK.min(until - now));
     }
+}
 
-    // Add User-Agent if specified
-    if let Some(ua) = &options.user_agent {
-        request = request.set("User-Agent", ua);
+/// Hand the whole command to the system's wget, for the `https` URLs the
+/// shell cannot speak
+fn external(args: &[String], cwd: &Path) -> Outcome {
+    let result = which::which("wget")
+        .map_err(|e| e.to_string())
+        .and_then(|path| {
+            Command::new(path)
+                .args(args)
+                .current_dir(cwd)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|e| io_message(&e))
+        });
+    match result {
+        Ok(output) => Outcome {
+            stdout: output.stdout,
+            stderr: output.stderr,
+            status: output.status.code().unwrap_or(1),
+        },
+        Err(e) => Outcome {
+            stderr: format!("wget: cannot run the system wget: {e}\n").into_bytes(),
+            status: 1,
+            ..Outcome::default()
+        },
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Set timeout if specified
-    if let Some(timeout) = options.timeout {
-        request = request.timeout(std::time::Duration::from_secs(timeout));
+    fn options(list: &[&str]) -> Result<Options, Usage> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
     }
 
-    let response = request
-        .call()
-        .with_context(|| format!("wget: failed to fetch {}", options.url))?;
+    #[test]
+    fn parses_gnu_style_options() {
+        let parsed = options(&[
+            "-rcq",
+            "-np",
+            "-l",
+            "inf",
+            "--limit-rate=20k",
+            "-Pout",
+            "-e",
+            "robots = off",
+            "host/a",
+            "host/b",
+        ])
+        .unwrap();
+        assert!(parsed.recursive && parsed.resume && parsed.no_parent);
+        assert_eq!(parsed.verbosity, Verbosity::Quiet);
+        assert_eq!(parsed.level, None);
+        assert_eq!(parsed.limit_rate, Some(20 * 1024));
+        assert_eq!(parsed.prefix.as_deref(), Some("out"));
+        assert!(!parsed.robots);
+        assert_eq!(parsed.urls, vec!["host/a", "host/b"]);
 
-    if response.status() != 200 {
-        return Err(anyhow!(
-            "wget: server responded with HTTP status {}",
-            response.status()
-        ));
+        assert_eq!(options(&[]).unwrap_err(), (1, "missing URL".to_string()));
+        assert_eq!(
+            options(&["-z", "u"]).unwrap_err(),
+            (2, "invalid option -- 'z'".to_string())
+        );
+        assert_eq!(
+            options(&["--tries=x", "u"]).unwrap_err(),
+            (2, "--tries: Invalid number 'x'.".to_string())
+        );
+        assert_eq!(
+            options(&["u", "-O"]).unwrap_err(),
+            (2, "option requires an argument -- 'O'".to_string())
+        );
     }
 
-    let body = response
-        .into_string()
-        .context("wget: failed to read response body")?;
+    #[test]
+    fn parses_rates_and_sizes() {
+        assert_eq!(parse_rate("1.5m"), Some(1_572_864));
+        assert_eq!(parse_rate("300"), Some(300));
+        assert_eq!(parse_rate("k"), None);
+        assert_eq!(human(512), "512");
+        assert_eq!(human(1536), "1.5K");
+        assert_eq!(human(34 << 20), "34M");
+    }
 
-    print!("{body}");
+    #[test]
+    fn follows_robots_rules() {
+        let robots = Robots::parse(
+            "User-agent: *\nDisallow: /private\nAllow: /private/open\n\n\
+             User-agent: other\nDisallow: /\n",
+        );
+        assert!(robots.allows("/index.html"));
+        assert!(!robots.allows("/private/x"));
+        assert!(robots.allows("/private/open/x"));
 
-    Ok(())
-}
+        let robots =
+            Robots::parse("User-agent: *\nDisallow:\n\nUser-agent: Wget\nDisallow: /cgi\n");
+        assert!(!robots.allows("/cgi/run"));
+        assert!(robots.allows("/other"));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn finds_links_in_order() {
+        let html = r##"<a href="one.html">1</a><img src='pic.png'> <a HREF=two.html>
+            <a href="#top"> <a href="mailto:x@y"> <a data-href="no"> <a href="q?a=1&amp;b=2">"##;
+        assert_eq!(
+            links(html),
+            vec!["one.html", "pic.png", "two.html", "q?a=1&b=2"]
+        );
+    }
 
     #[test]
-    fn test_parse_wget_args() {
-        let args = vec!["https://example.com/file.txt".to_string()];
-        let options = parse_wget_args(&args).expect("Failed to parse valid wget args");
-        assert_eq!(options.url, "https://example.com/file.txt");
-        assert!(options.output.is_none());
-
-        let args = vec![
-            "-O".to_string(),
-            "output.txt".to_string(),
-            "https://example.com/file.txt".to_string(),
-        ];
-        let options = parse_wget_args(&args).expect("Failed to parse wget args with output option");
-        assert_eq!(options.output, Some("output.txt".to_string()));
+    fn names_files_from_urls() {
+        let url = |text: &str| Url::parse(text).unwrap();
+        assert_eq!(remote_name(&url("http://h/a/b%20c.txt")), "b c.txt");
+        assert_eq!(remote_name(&url("http://h/dir/")), "index.html");
+        assert!(below(
+            &url("http://h/docs/index.html"),
+            &url("http://h/docs/a/b")
+        ));
+        assert!(!below(
+            &url("http://h/docs/index.html"),
+            &url("http://h/other")
+        ));
     }
 }
@@ -1,160 +1,746 @@
-use std::process::Command;
+//! `xargs` builtin - build and run command lines from standard input
+//!
+//! Syntax:
+//!   xargs [OPTION]... [COMMAND [INITIAL-ARGS]...]
+//!
+//! Items are read from standard input (or `-a FILE`) and appended to
+//! COMMAND (`echo` by default), as many per command line as the platform's
+//! argument size limit and `-n`/`-L`/`-s` allow. By default items are
+//! separated by blanks and newlines, and may be quoted with `'`, `"` or a
+//! backslash; `-0` and `-d` split on a single delimiter instead, and `-I`
+//! runs the command once per line with the line in place of a string.
+//!
+//! Options:
+//!   -0, --null               items end with NUL
+//!   -d, --delimiter=DELIM    items end with DELIM
+//!   -a, --arg-file=FILE      read items from FILE
+//!   -E EOF                   stop reading at the item EOF
+//!   -I REPLACE               replace REPLACE in INITIAL-ARGS with each line
+//!   -L, --max-lines=N        use N input lines per command line
+//!   -n, --max-args=N         use at most N items per command line
+//!   -s, --max-chars=N        limit command lines to N bytes
+//!   -P, --max-procs=N        run up to N commands at a time (0: no limit)
+//!   -r, --no-run-if-empty    do not run COMMAND when there are no items
+//!   -t, --verbose            print each command line before running it
+//!   -x, --exit               fail when a line of -n/-L items is too long
+//!
+//! The exit status is 123 when a command exited with status 1-125, 124 when
+//! one exited with 255, 125 when one was killed by a signal, 126 when the
+//! command cannot be run and 127 when it is not found; the last three stop
+//! xargs from starting more commands.
+
+use crate::common::io_message;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
 use std::io::Read;
-use nxsh_core::{ShellError, ErrorKind}; use nxsh_core::error::RuntimeErrorKind;
-use super::ui_design::{Colorize, ColorPalette, Icons};
-
-pub fn xargs_cli(args: &[String]) -> Result<(), ShellError> {
-    if args.contains(&"--help".to_string()) {
-        print_help();
-        return Ok(());
-    }
-
-    let mut max_args = None;
-    let mut null_terminated = false;
-    let mut interactive = false;
-    let mut command_args = Vec::new();
-    let mut delimiter = None;
-    
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-n" => {
-                if i + 1 < args.len() {
-                    max_args = Some(args[i + 1].parse().unwrap_or(1));
-                    i += 1;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// The `xargs` builtin command implementation
+pub struct XargsCommand;
+
+impl Builtin for XargsCommand {
+    fn name(&self) -> &'static str {
+        "xargs"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Build and run command lines from standard input"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read items from standard input and run a command with as many of them as fit \
+         on each command line, optionally several commands at a time."
+    }
+
+    fn usage(&self) -> &'static str {
+        "xargs [-0 | -d DELIM] [-I REPLACE | -L N | -n N] [-P N] [-rtx] [COMMAND [ARG]...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Build and run command lines from standard input. Use 'find . -print0 | xargs -0 -P 4 gzip'."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match Options::parse(args) {
+            Ok(options) => options,
+            Err(message) => return Ok(failure(1, message)),
+        };
+        let mut input = Vec::new();
+        let read = match &options.arg_file {
+            Some(file) => std::fs::read(ctx.cwd.join(file))
+                .map(|data| input = data)
+                .map_err(|e| format!("{file}: {}", io_message(&e))),
+            None => ctx
+                .stdin
+                .read_to_end(&mut input)
+                .map(|_| ())
+                .map_err(|e| io_message(&e)),
+        };
+        if let Err(message) = read {
+            return Ok(failure(1, message));
+        }
+        let text = String::from_utf8_lossy(&input);
+        let units = match split_input(&text, &options) {
+            Ok(units) => units,
+            Err(message) => return Ok(failure(1, message)),
+        };
+
+        let env: Vec<(String, String)> = match ctx.env.read() {
+            Ok(env) => env.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        let limit = options
+            .max_chars
+            .unwrap_or(DEFAULT_MAX_CHARS)
+            .min(system_limit(&env));
+        let lines = match build(&options, units, limit) {
+            Ok(lines) => lines,
+            Err(message) => return Ok(failure(1, message)),
+        };
+        let program = ctx
+            .resolve_command(&options.command[0])
+            .unwrap_or_else(|| options.command[0].clone().into());
+        let job = Job {
+            program,
+            env,
+            cwd: ctx.cwd.clone(),
+            verbose: options.verbose,
+        };
+        let outcome = job.run(lines, options.max_procs);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+fn failure(status: i32, message: String) -> ExecutionResult {
+    ExecutionResult::failure(status).with_error(format!("xargs: {message}\n").into_bytes())
+}
+
+/// GNU xargs' default command line size
+const DEFAULT_MAX_CHARS: usize = 128 * 1024;
+
+/// How input is cut into items
+#[derive(Debug, Clone, PartialEq)]
+enum Split {
+    /// Blanks and newlines, with quoting
+    Blanks,
+    /// A single delimiter, taken literally
+    Delimiter(char),
+}
+
+#[derive(Debug)]
+struct Options {
+    split: Split,
+    arg_file: Option<String>,
+    eof: Option<String>,
+    replace: Option<String>,
+    max_lines: Option<usize>,
+    max_args: Option<usize>,
+    max_chars: Option<usize>,
+    max_procs: usize,
+    no_run_if_empty: bool,
+    verbose: bool,
+    exit_if_too_long: bool,
+    command: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            split: Split::Blanks,
+            arg_file: None,
+            eof: None,
+            replace: None,
+            max_lines: None,
+            max_args: None,
+            max_chars: None,
+            max_procs: 1,
+            no_run_if_empty: false,
+            verbose: false,
+            exit_if_too_long: false,
+            command: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let flag = match name {
+                    "null" => '0',
+                    "delimiter" => 'd',
+                    "arg-file" => 'a',
+                    "eof" => 'E',
+                    "replace" => 'I',
+                    "max-lines" => 'L',
+                    "max-args" => 'n',
+                    "max-chars" => 's',
+                    "max-procs" => 'P',
+                    "no-run-if-empty" => 'r',
+                    "verbose" => 't',
+                    "exit" => 'x',
+                    _ => return Err(format!("unrecognized option '{arg}'")),
+                };
+                let value = if "0rtx".contains(flag) {
+                    None
+                } else {
+                    Some(match inline {
+                        Some(value) => value,
+                        None => iter
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("option '--{name}' requires an argument"))?,
+                    })
+                };
+                options.apply(flag, value)?;
+                continue;
+            }
+            let Some(cluster) = arg.strip_prefix('-').filter(|rest| !rest.is_empty()) else {
+                options.command.push(arg.clone());
+                break;
+            };
+            for (at, flag) in cluster.char_indices() {
+                if !"0rtxdaEeIiLlnsP".contains(flag) {
+                    return Err(format!("invalid option -- '{flag}'"));
                 }
-            },
-            "-0" => null_terminated = true,
-            "-p" => interactive = true,
-            "-d" => {
-                if i + 1 < args.len() {
-                    delimiter = Some(args[i + 1].chars().next().unwrap_or('\n'));
-                    i += 1;
+                if "0rtx".contains(flag) {
+                    options.apply(flag, None)?;
+                    continue;
                 }
-            },
-            arg if !arg.starts_with('-') => command_args.push(arg.to_string()),
-            _ => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("xargs: invalid option: {}", args[i]))),
+                let rest = &cluster[at + flag.len_utf8()..];
+                let value = match flag {
+                    // `-i`, `-l` and `-e` only take an attached value
+                    'i' | 'l' | 'e' => rest.to_string(),
+                    _ if !rest.is_empty() => rest.to_string(),
+                    _ => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option requires an argument -- '{flag}'"))?,
+                };
+                options.apply(flag, Some(value))?;
+                break;
+            }
         }
-        i += 1;
+        options.command.extend(iter.cloned());
+        if options.command.is_empty() {
+            options.command.push("echo".to_string());
+        }
+        Ok(options)
+    }
+
+    fn apply(&mut self, flag: char, value: Option<String>) -> Result<(), String> {
+        let value = value.unwrap_or_default();
+        let count = |value: &str, min: usize| -> Result<usize, String> {
+            match value.parse::<usize>() {
+                Ok(n) if n >= min => Ok(n),
+                _ => Err(format!("invalid number \"{value}\" for -{flag} option")),
+            }
+        };
+        match flag {
+            '0' => self.split = Split::Delimiter('\0'),
+            'r' => self.no_run_if_empty = true,
+            't' => self.verbose = true,
+            'x' => self.exit_if_too_long = true,
+            'd' => self.split = Split::Delimiter(delimiter(&value)?),
+            'a' => self.arg_file = Some(value),
+            'E' | 'e' => self.eof = Some(value).filter(|eof| !eof.is_empty()),
+            'I' | 'i' => {
+                let replace = if value.is_empty() && flag == 'i' {
+                    "{}".to_string()
+                } else {
+                    value
+                };
+                self.replace = Some(replace);
+                self.max_lines = None;
+                self.max_args = None;
+            }
+            'L' | 'l' => {
+                self.max_lines = Some(if value.is_empty() && flag == 'l' {
+                    1
+                } else {
+                    count(&value, 1)?
+                });
+                self.max_args = None;
+                self.replace = None;
+            }
+            'n' => {
+                self.max_args = Some(count(&value, 1)?);
+                self.max_lines = None;
+            }
+            's' => self.max_chars = Some(count(&value, 1)?),
+            'P' => {
+                let n = count(&value, 0)?;
+                self.max_procs = if n == 0 { usize::MAX } else { n };
+            }
+            other => return Err(format!("invalid option -- '{other}'")),
+        }
+        Ok(())
     }
-    
-    let command = command_args.first().unwrap_or(&"echo".to_string()).clone();
-    let cmd_args = if command_args.len() > 1 { &command_args[1..] } else { &[] };
-    
-    execute_xargs(&command, cmd_args, max_args, null_terminated, interactive, delimiter)
 }
 
-fn print_help() {
-    println!("xargs - build and execute command lines from standard input
+/// The character of a `-d` argument, which may be an escape like `\n`
+fn delimiter(text: &str) -> Result<char, String> {
+    let mut chars = text.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => return Ok(c),
+        (Some('\\'), Some(c)) => c,
+        _ => return Err(format!("invalid input delimiter specification {text}")),
+    };
+    let rest = chars.as_str();
+    let escaped = match (c, rest) {
+        ('n', "") => '\n',
+        ('t', "") => '\t',
+        ('r', "") => '\r',
+        ('0', "") => '\0',
+        ('\\', "") => '\\',
+        ('x', hex) if !hex.is_empty() => u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid escape sequence {text} in input delimiter"))?,
+        ('0'..='7', _) => u32::from_str_radix(&text[1..], 8)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid escape sequence {text} in input delimiter"))?,
+        _ => return Err(format!("invalid escape sequence {text} in input delimiter")),
+    };
+    Ok(escaped)
+}
 
-USAGE:
-    xargs [OPTIONS] [COMMAND [INITIAL-ARGS]]
+/// Cut input into units, each the items of one argument or, with `-L` and
+/// `-I`, of one line
+fn split_input(text: &str, options: &Options) -> Result<Vec<Vec<String>>, String> {
+    if let Split::Delimiter(delimiter) = options.split {
+        let mut items: Vec<&str> = text.split(delimiter).collect();
+        if items.last() == Some(&"") {
+            items.pop();
+        }
+        return Ok(items
+            .into_iter()
+            .map(|item| vec![item.to_string()])
+            .collect());
+    }
+    let by_line = options.max_lines.is_some() || options.replace.is_some();
+    let mut units = Vec::new();
+    for line in text.lines() {
+        if options.replace.is_some() {
+            // The whole line is one item, without its leading blanks
+            let item = line.trim_start_matches([' ', '\t']);
+            if options.eof.as_deref() == Some(item) {
+                break;
+            }
+            if !item.is_empty() {
+                units.push(vec![item.to_string()]);
+            }
+            continue;
+        }
+        let mut items = Vec::new();
+        let mut stop = false;
+        for item in split_blanks(line)? {
+            if options.eof.as_ref() == Some(&item) {
+                stop = true;
+                break;
+            }
+            items.push(item);
+        }
+        if by_line {
+            if !items.is_empty() {
+                units.push(items);
+            }
+        } else {
+            units.extend(items.into_iter().map(|item| vec![item]));
+        }
+        if stop {
+            break;
+        }
+    }
+    Ok(units)
+}
 
-OPTIONS:
-    -n MAX-ARGS    Use at most MAX-ARGS arguments per command line
-    -0             Input items are terminated by null, not whitespace
-    -p             Prompt before running each command
-    -d DELIM       Use DELIM as delimiter instead of whitespace
-    -h, --help     Show this help
+/// Split a line on blanks, honouring quotes and backslashes
+fn split_blanks(line: &str) -> Result<Vec<String>, String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut started = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if started {
+                    items.push(std::mem::take(&mut item));
+                    started = false;
+                }
+            }
+            '\'' | '"' => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(other) => item.push(other),
+                        None => {
+                            let kind = if c == '\'' { "single" } else { "double" };
+                            return Err(format!(
+                                "unmatched {kind} quote; by default quotes are special to xargs unless you use the -0 option"
+                            ));
+                        }
+                    }
+                }
+            }
+            '\\' => {
+                started = true;
+                if let Some(next) = chars.next() {
+                    item.push(next);
+                }
+            }
+            other => {
+                started = true;
+                item.push(other);
+            }
+        }
+    }
+    if started {
+        items.push(item);
+    }
+    Ok(items)
+}
 
-EXAMPLES:
-    # Remove files listed in stdin
-    ls *.tmp | xargs rm
-    
-    # Process with limited arguments per call
-    echo \"1 2 3 4 5\" | xargs -n 2 echo
-    
-    # Handle filenames with spaces (null-terminated)
-    find . -name \"*.txt\" -print0 | xargs -0 grep \"pattern\"");
+/// The command line size the platform allows, less the environment and
+/// some headroom
+fn system_limit(env: &[(String, String)]) -> usize {
+    let environment: usize = env
+        .iter()
+        .map(|(k, v)| k.len() + v.len() + 2 + std::mem::size_of::<usize>())
+        .sum();
+    #[cfg(unix)]
+    let max = {
+        // SAFETY: sysconf only reads a system constant
+        let max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+        if max > 0 {
+            max as usize
+        } else {
+            DEFAULT_MAX_CHARS
+        }
+    };
+    // CreateProcess takes at most 32767 UTF-16 units
+    #[cfg(not(unix))]
+    let max = 32767 + environment;
+    max.saturating_sub(environment + 2048).max(4096)
 }
 
-fn execute_xargs(
-    command: &str, 
-    base_args: &[String], 
-    max_args: Option<usize>,
-    null_terminated: bool,
-    interactive: bool,
-    delimiter: Option<char>
-) -> Result<(), ShellError> {
-    use std::io::{self, BufRead};
-    
-    let stdin = io::stdin();
-    let mut input_args = Vec::new();
-    
-    // Read input arguments
-    if null_terminated {
-        // Buffered read of stdin to avoid clippy::unbuffered_bytes
-        let mut reader = io::BufReader::new(stdin.lock());
-        let mut buffer = String::new();
-        let mut tmp = Vec::new();
-        // Read all remaining bytes into tmp (typically small for xargs use cases)
-        if let Err(e) = reader.read_to_end(&mut tmp) {
-            return Err(ShellError::new(
-                ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
-                format!("xargs: {e}"),
-            ));
-        }
-        for b in tmp {
-            if b == 0 { // NULL terminator
-                if !buffer.is_empty() {
-                    input_args.push(buffer.clone());
-                    buffer.clear();
-                }
-            } else {
-                buffer.push(char::from(b));
-            }
-        }
-        if !buffer.is_empty() { input_args.push(buffer); }
-    } else {
-        let delim = delimiter.unwrap_or(' ');
-        for line in stdin.lock().lines() {
-            match line {
-                Ok(line) => {
-                    let parts: Vec<&str> = if delim == ' ' {
-                        line.split_whitespace().collect()
+/// The bytes an argument takes on a command line
+fn size(arg: &str) -> usize {
+    arg.len() + 1 + std::mem::size_of::<usize>()
+}
+
+/// Group units into command lines
+fn build(
+    options: &Options,
+    units: Vec<Vec<String>>,
+    limit: usize,
+) -> Result<Vec<Vec<String>>, String> {
+    let base: usize = options.command.iter().map(|arg| size(arg)).sum();
+    if base > limit {
+        return Err("argument line too long".to_string());
+    }
+    if units.is_empty() {
+        return Ok(if options.no_run_if_empty {
+            Vec::new()
+        } else {
+            vec![options.command.clone()]
+        });
+    }
+    if let Some(replace) = &options.replace {
+        let mut lines = Vec::new();
+        for unit in units {
+            let line: Vec<String> = options
+                .command
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| {
+                    // The command name itself is never replaced
+                    if i == 0 {
+                        arg.clone()
                     } else {
-                        line.split(delim).collect()
+                        arg.replace(replace.as_str(), &unit[0])
+                    }
+                })
+                .collect();
+            if line.iter().map(|arg| size(arg)).sum::<usize>() > limit {
+                return Err("argument line too long".to_string());
+            }
+            lines.push(line);
+        }
+        return Ok(lines);
+    }
+    let per_line = options.max_args.or(options.max_lines);
+    let mut lines = Vec::new();
+    let mut line = options.command.clone();
+    let mut used = base;
+    let mut count = 0;
+    for unit in units {
+        let extra: usize = unit.iter().map(|arg| size(arg)).sum();
+        if count > 0 && used + extra > limit {
+            if options.exit_if_too_long && per_line.is_some() {
+                return Err("argument line too long".to_string());
+            }
+            lines.push(std::mem::replace(&mut line, options.command.clone()));
+            used = base;
+            count = 0;
+        }
+        if base + extra > limit {
+            return Err("argument line too long".to_string());
+        }
+        used += extra;
+        count += 1;
+        line.extend(unit);
+        if per_line == Some(count) {
+            lines.push(std::mem::replace(&mut line, options.command.clone()));
+            used = base;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// What is needed to start the commands, shareable across threads
+struct Job {
+    program: PathBuf,
+    env: Vec<(String, String)>,
+    cwd: PathBuf,
+    verbose: bool,
+}
+
+/// The result of one command line
+struct Finished {
+    trace: String,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Exited(i32),
+    Signaled,
+    NotFound,
+    CannotRun,
+}
+
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Job {
+    /// Run the command lines, up to MAX_PROCS at a time; output is collected
+    /// in the order the commands finish
+    fn run(&self, lines: Vec<Vec<String>>, max_procs: usize) -> Outcome {
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let workers = max_procs.min(lines.len()).max(1);
+        let (sender, receiver) = mpsc::channel();
+        let mut outcome = Outcome {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            status: 0,
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                let sender = sender.clone();
+                let (lines, next, stop) = (&lines, &next, &stop);
+                scope.spawn(move || loop {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(line) = lines.get(index) else {
+                        break;
                     };
-                    for part in parts {
-                        if !part.is_empty() {
-                            input_args.push(part.to_string());
-                        }
+                    let finished = self.spawn(line);
+                    if !matches!(finished.status, Status::Exited(0..=254)) {
+                        stop.store(true, Ordering::SeqCst);
                     }
-                },
-                Err(e) => return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("xargs: {e}"))),
-            }
-        }
-    }
-    
-    // Execute commands
-    let chunk_size = max_args.unwrap_or(input_args.len().max(1));
-    
-    for chunk in input_args.chunks(chunk_size) {
-        let mut full_args = base_args.to_vec();
-        full_args.extend(chunk.iter().cloned());
-        
-        if interactive {
-            print!("Execute: {} {}? (y/N): ", command, full_args.join(" "));
-            let mut response = String::new();
-            io::stdin().read_line(&mut response).ok();
-            if !response.trim().to_lowercase().starts_with('y') {
-                continue;
+                    if sender.send(finished).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+            for finished in receiver {
+                outcome.stderr.extend(finished.trace.into_bytes());
+                outcome.stdout.extend(finished.stdout);
+                outcome.stderr.extend(finished.stderr);
+                let name = self.program.display();
+                let (status, notice) = match finished.status {
+                    Status::Exited(0) => (0, None),
+                    Status::Exited(255) => (
+                        124,
+                        Some(format!("{name}: exited with status 255; aborting")),
+                    ),
+                    Status::Exited(_) => (123, None),
+                    Status::Signaled => (125, Some(format!("{name}: terminated by signal"))),
+                    Status::NotFound => (127, Some(format!("{name}: No such file or directory"))),
+                    Status::CannotRun => (126, Some(format!("{name}: Permission denied"))),
+                };
+                if let Some(notice) = notice {
+                    outcome
+                        .stderr
+                        .extend(format!("xargs: {notice}\n").into_bytes());
+                }
+                outcome.status = outcome.status.max(status);
             }
+        });
+        outcome
+    }
+
+    fn spawn(&self, line: &[String]) -> Finished {
+        let trace = if self.verbose {
+            format!("{}\n", line.join(" "))
+        } else {
+            String::new()
+        };
+        let mut command = std::process::Command::new(&self.program);
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::arg0(&mut command, &line[0]);
+        command
+            .args(&line[1..])
+            .current_dir(&self.cwd)
+            .stdin(std::process::Stdio::null());
+        for (key, value) in &self.env {
+            command.env(key, value);
         }
-        
-        match Command::new(command).args(&full_args).status() {
-            Ok(status) if !status.success() => {
-                eprintln!("xargs: {} failed with exit code {:?}", command, status.code());
+        match command.output() {
+            Ok(output) => Finished {
+                trace,
+                stdout: output.stdout,
+                stderr: output.stderr,
+                status: match output.status.code() {
+                    Some(code) => Status::Exited(code),
+                    None => Status::Signaled,
+                },
             },
-            Err(e) => {
-                return Err(ShellError::new(ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument), format!("xargs: {command}: {e}")));
+            Err(e) => Finished {
+                trace,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+                status: match e.kind() {
+                    std::io::ErrorKind::NotFound => Status::NotFound,
+                    _ => Status::CannotRun,
+                },
             },
-            _ => {}
         }
     }
-    
-    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn options(list: &[&str]) -> Options {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args).unwrap()
+    }
+
+    fn lines(list: &[&str], input: &str, limit: usize) -> Result<Vec<Vec<String>>, String> {
+        let options = options(list);
+        let units = split_input(input, &options)?;
+        build(&options, units, limit)
+    }
+
+    #[test]
+    fn parses_options_and_command() {
+        let parsed = options(&["-0rt", "-n2", "-P", "0", "grep", "-n", "x"]);
+        assert_eq!(parsed.split, Split::Delimiter('\0'));
+        assert!(parsed.no_run_if_empty && parsed.verbose);
+        assert_eq!(parsed.max_args, Some(2));
+        assert_eq!(parsed.max_procs, usize::MAX);
+        assert_eq!(parsed.command, ["grep", "-n", "x"]);
+        assert_eq!(options(&["-i"]).replace.as_deref(), Some("{}"));
+        assert_eq!(options(&["--delimiter=\\n"]).split, Split::Delimiter('\n'));
+        assert_eq!(options(&[]).command, ["echo"]);
+        assert!(Options::parse(&["-n".to_string(), "0".to_string()]).is_err());
+        assert!(Options::parse(&["-q".to_string()]).is_err());
+    }
+
+    #[test]
+    fn splits_quoted_and_delimited_input() {
+        assert_eq!(
+            split_blanks(r#"a 'b c' "d e"f g\ h"#).unwrap(),
+            ["a", "b c", "d ef", "g h"]
+        );
+        assert!(split_blanks("'open").is_err());
+        let all = lines(&["-n", "2", "echo"], "1 2 3\n4 5\n", 4096).unwrap();
+        assert_eq!(
+            all,
+            [
+                vec!["echo", "1", "2"],
+                vec!["echo", "3", "4"],
+                vec!["echo", "5"]
+            ]
+        );
+        let all = lines(&["-L", "1"], "a b\n\nc\n", 4096).unwrap();
+        assert_eq!(all, [vec!["echo", "a", "b"], vec!["echo", "c"]]);
+        let all = lines(&["-0"], "x y\0z\0", 4096).unwrap();
+        assert_eq!(all, [vec!["echo", "x y", "z"]]);
+        let all = lines(&["-I", "%", "mv", "%", "%.bak"], "  a b\nc\n", 4096).unwrap();
+        assert_eq!(
+            all,
+            [vec!["mv", "a b", "a b.bak"], vec!["mv", "c", "c.bak"]]
+        );
+        let all = lines(&["-E", "STOP"], "a STOP b\n", 4096).unwrap();
+        assert_eq!(all, [vec!["echo", "a"]]);
+    }
 
+    #[test]
+    fn respects_size_limits_and_empty_input() {
+        let item = size("aaaa");
+        let limit = size("echo") + 2 * item;
+        let all = lines(&[], "aaaa aaaa aaaa", limit).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(lines(&["-x", "-n", "3"], "aaaa aaaa aaaa", limit).is_err());
+        assert!(lines(&[], &"a".repeat(100), limit).is_err());
+        assert_eq!(lines(&[], "", limit).unwrap(), [vec!["echo"]]);
+        assert!(lines(&["-r"], "  \n", limit).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_commands_in_parallel_and_aggregates_status() {
+        let job = |program: &str| Job {
+            program: PathBuf::from(program),
+            env: Vec::new(),
+            cwd: std::env::temp_dir(),
+            verbose: false,
+        };
+        let batches = |items: &[&str]| -> Vec<Vec<String>> {
+            items
+                .iter()
+                .map(|item| vec!["sh".to_string(), "-c".to_string(), item.to_string()])
+                .collect()
+        };
+        let outcome = job("/bin/sh").run(batches(&["sleep 0.2; echo slow", "echo fast"]), 2);
+        assert_eq!(String::from_utf8(outcome.stdout).unwrap(), "fast\nslow\n");
+        assert_eq!(outcome.status, 0);
+
+        let outcome = job("/bin/sh").run(batches(&["exit 3", "echo ok"]), 1);
+        assert_eq!(outcome.status, 123);
+        assert_eq!(String::from_utf8(outcome.stdout).unwrap(), "ok\n");
+
+        let outcome = job("/bin/sh").run(batches(&["exit 255", "echo never"]), 1);
+        assert_eq!(outcome.status, 124);
+        assert_eq!(outcome.stdout, b"");
+
+        let outcome = job("/nonexistent/command").run(vec![vec!["x".to_string()]], 1);
+        assert_eq!(outcome.status, 127);
+    }
+}
@@ -0,0 +1,452 @@
+//! `getfattr` and `setfattr` builtins - read and change extended attributes
+//!
+//! Syntax:
+//!   getfattr [-h] [-d] [-n NAME] [-m PATTERN] [-e ENCODING] [--only-values]
+//!            [--absolute-names] FILE...
+//!   setfattr [-h] -n NAME [-v VALUE] FILE...
+//!   setfattr [-h] -x NAME FILE...
+//!
+//! getfattr prints a `# file:` header for each file followed by the names of
+//! its attributes that match PATTERN, a regular expression that defaults to
+//! `^user\.` (`-m -` matches every name). With `-d` the values are printed
+//! as well, and `-n` prints the one named attribute whatever the pattern.
+//!
+//!   -e ENCODING     print values as `text` (quoted, the default), `hex`
+//!                   (`0x…`) or `base64` (`0s…`)
+//!   -h              work on symbolic links themselves, not their targets
+//!   --only-values   print the bare value of each attribute and nothing else
+//!   --absolute-names  keep the leading `/` of absolute names
+//!
+//! setfattr sets attribute NAME to VALUE, or to an empty value, or removes
+//! it with `-x`. VALUE is taken as hex after `0x`, as base64 after `0s`, as
+//! text with backslash escapes inside double quotes, and literally otherwise.
+//!
+//! Relative names are taken from the shell's working directory. The exit
+//! status is 0 on success, 1 if any file failed and 2 on usage errors.
+
+use crate::common::{hal_message, BuiltinContext, BuiltinResult};
+use base64::Engine as _;
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use nxsh_hal::fs::{get_xattr, list_xattrs, remove_xattr, set_xattr};
+use regex::Regex;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::Path;
+
+const GETFATTR_USAGE: &str =
+    "getfattr [-h] [-d] [-n NAME] [-m PATTERN] [-e ENCODING] [--only-values] [--absolute-names] FILE...";
+const SETFATTR_USAGE: &str = "setfattr [-h] -n NAME [-v VALUE] | -x NAME FILE...";
+
+/// The `getfattr` builtin command implementation
+pub struct GetfattrCommand;
+
+/// The `setfattr` builtin command implementation
+pub struct SetfattrCommand;
+
+impl Builtin for GetfattrCommand {
+    fn name(&self) -> &'static str {
+        "getfattr"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print the extended attributes of files"
+    }
+
+    fn description(&self) -> &'static str {
+        "List the extended attributes of each file whose names match a pattern, with their \
+         values when dumping, or print one named attribute."
+    }
+
+    fn usage(&self) -> &'static str {
+        GETFATTR_USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Inspect extended attributes. Use 'getfattr -d FILE' to see the user attributes with \
+         their values or 'getfattr -d -m - FILE' to include every namespace."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = getfattr(args, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+impl Builtin for SetfattrCommand {
+    fn name(&self) -> &'static str {
+        "setfattr"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Set or remove extended attributes of files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set an extended attribute of each file to a value given as text, hex or base64, or \
+         remove it."
+    }
+
+    fn usage(&self) -> &'static str {
+        SETFATTR_USAGE
+    }
+
+    fn help(&self) -> &'static str {
+        "Change extended attributes. Use 'setfattr -n user.comment -v draft FILE' to set one \
+         or 'setfattr -x user.comment FILE' to remove it."
+    }
+
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let outcome = setfattr(args, &ctx.cwd);
+        Ok(ExecutionResult::success(outcome.status)
+            .with_output(outcome.stdout)
+            .with_error(outcome.stderr))
+    }
+}
+
+/// Run getfattr for the legacy dispatcher
+pub fn execute_getfattr(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = getfattr(args, &std::env::current_dir()?);
+    outcome.write()
+}
+
+/// Run setfattr for the legacy dispatcher
+pub fn execute_setfattr(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
+    let outcome = setfattr(args, &std::env::current_dir()?);
+    outcome.write()
+}
+
+/// What a run printed and its exit status
+#[derive(Default)]
+struct Outcome {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Outcome {
+    fn usage(command: &str, message: &str, usage: &str) -> Self {
+        Outcome {
+            stdout: Vec::new(),
+            stderr: format!("{command}: {message}\n{command}: usage: {usage}\n").into_bytes(),
+            status: 2,
+        }
+    }
+
+    /// Report a failure with one file and carry on with the next
+    fn fail(&mut self, command: &str, message: &str) {
+        self.stderr
+            .extend_from_slice(format!("{command}: {message}\n").as_bytes());
+        self.status = 1;
+    }
+
+    fn write(self) -> BuiltinResult<i32> {
+        io::stdout().write_all(&self.stdout)?;
+        io::stderr().write_all(&self.stderr)?;
+        Ok(self.status)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Text,
+    Hex,
+    Base64,
+}
+
+fn getfattr(args: &[String], cwd: &Path) -> Outcome {
+    let usage = |message: &str| Outcome::usage("getfattr", message, GETFATTR_USAGE);
+    let mut dump = false;
+    let mut name = None;
+    let mut pattern = None;
+    let mut encoding = None;
+    let mut follow = true;
+    let mut only_values = false;
+    let mut absolute = false;
+    let mut rest = args;
+    while let Some(arg) = rest
+        .first()
+        .filter(|arg| arg.starts_with('-') && arg.len() > 1)
+    {
+        rest = &rest[1..];
+        let mut value = || {
+            rest.split_first().map(|(value, tail)| {
+                rest = tail;
+                value.clone()
+            })
+        };
+        match arg.as_str() {
+            "--" => break,
+            "-d" | "--dump" => dump = true,
+            "-h" | "--no-dereference" => follow = false,
+            "--only-values" => only_values = true,
+            "--absolute-names" => absolute = true,
+            "-n" | "--name" | "-m" | "--match" | "-e" | "--encoding" => {
+                let Some(value) = value() else {
+                    return usage(&format!("{arg}: option requires an argument"));
+                };
+                match arg.as_str() {
+                    "-n" | "--name" => name = Some(value),
+                    "-m" | "--match" => pattern = Some(value),
+                    _ => {
+                        encoding = Some(match value.as_str() {
+                            "text" => Encoding::Text,
+                            "hex" => Encoding::Hex,
+                            "base64" => Encoding::Base64,
+                            _ => return usage(&format!("{value}: unknown encoding")),
+                        })
+                    }
+                }
+            }
+            _ => return usage(&format!("{arg}: invalid option")),
+        }
+    }
+    if rest.is_empty() {
+        return usage("missing file operand");
+    }
+    // `-` stands for every name
+    let pattern = match pattern.as_deref() {
+        Some("-") => Regex::new(""),
+        Some(pattern) => Regex::new(pattern),
+        None => Regex::new(r"^user\."),
+    };
+    let pattern = match pattern {
+        Ok(pattern) => pattern,
+        Err(e) => return usage(&format!("invalid pattern: {e}")),
+    };
+    // Values are shown once asked for, as text unless told otherwise
+    let show_values = dump || name.is_some() || only_values || encoding.is_some();
+    let encoding = encoding.unwrap_or(Encoding::Text);
+
+    let mut outcome = Outcome::default();
+    let mut warned = false;
+    for file in rest {
+        let path = cwd.join(file);
+        let names = match &name {
+            Some(name) => vec![name.into()],
+            None => match list_xattrs(&path, follow) {
+                Ok(mut names) => {
+                    names.retain(|name| pattern.is_match(&name.to_string_lossy()));
+                    names.sort();
+                    names
+                }
+                Err(e) => {
+                    outcome.fail("getfattr", &format!("{file}: {}", hal_message(&e)));
+                    continue;
+                }
+            },
+        };
+        let mut lines = Vec::new();
+        for attribute in &names {
+            if !show_values {
+                lines.extend_from_slice(attribute.to_string_lossy().as_bytes());
+                lines.push(b'\n');
+                continue;
+            }
+            let value = match get_xattr(&path, attribute, follow) {
+                Ok(Some(value)) => value,
+                Ok(None) => {
+                    let message =
+                        format!("{file}: {}: No such attribute", attribute.to_string_lossy());
+                    outcome.fail("getfattr", &message);
+                    continue;
+                }
+                Err(e) => {
+                    outcome.fail("getfattr", &format!("{file}: {}", hal_message(&e)));
+                    continue;
+                }
+            };
+            if only_values {
+                lines.extend_from_slice(&value);
+            } else {
+                let line = format!(
+                    "{}={}\n",
+                    attribute.to_string_lossy(),
+                    encode(&value, encoding)
+                );
+                lines.extend_from_slice(line.as_bytes());
+            }
+        }
+        if lines.is_empty() || only_values {
+            outcome.stdout.extend_from_slice(&lines);
+            continue;
+        }
+        let shown = match file.strip_prefix('/') {
+            Some(relative) if !absolute => {
+                if !warned {
+                    warned = true;
+                    outcome.stderr.extend_from_slice(
+                        b"getfattr: Removing leading '/' from absolute path names\n",
+                    );
+                }
+                relative
+            }
+            _ => file.as_str(),
+        };
+        outcome
+            .stdout
+            .extend_from_slice(format!("# file: {shown}\n").as_bytes());
+        outcome.stdout.extend_from_slice(&lines);
+        outcome.stdout.push(b'\n');
+    }
+    outcome
+}
+
+fn setfattr(args: &[String], cwd: &Path) -> Outcome {
+    let usage = |message: &str| Outcome::usage("setfattr", message, SETFATTR_USAGE);
+    let mut name = None;
+    let mut remove = None;
+    let mut value = None;
+    let mut follow = true;
+    let mut rest = args;
+    while let Some(arg) = rest
+        .first()
+        .filter(|arg| arg.starts_with('-') && arg.len() > 1)
+    {
+        rest = &rest[1..];
+        let mut operand = || {
+            rest.split_first().map(|(value, tail)| {
+                rest = tail;
+                value.clone()
+            })
+        };
+        match arg.as_str() {
+            "--" => break,
+            "-h" | "--no-dereference" => follow = false,
+            "-n" | "--name" | "-x" | "--remove" | "-v" | "--value" => {
+                let Some(operand) = operand() else {
+                    return usage(&format!("{arg}: option requires an argument"));
+                };
+                match arg.as_str() {
+                    "-n" | "--name" => name = Some(operand),
+                    "-x" | "--remove" => remove = Some(operand),
+                    _ => value = Some(operand),
+                }
+            }
+            _ => return usage(&format!("{arg}: invalid option")),
+        }
+    }
+    let change = match (name, remove, value) {
+        (Some(name), None, value) => {
+            let value = match value.as_deref().map(decode).transpose() {
+                Ok(value) => value.unwrap_or_default(),
+                Err(message) => return usage(&message),
+            };
+            Ok((name, value))
+        }
+        (None, Some(name), None) => Err(name),
+        (Some(_), Some(_), _) => return usage("-n and -x cannot be combined"),
+        (None, Some(_), Some(_)) => return usage("-v needs -n"),
+        (None, None, _) => return usage("missing -n or -x"),
+    };
+    if rest.is_empty() {
+        return usage("missing file operand");
+    }
+
+    let mut outcome = Outcome::default();
+    for file in rest {
+        let path = cwd.join(file);
+        let result = match &change {
+            Ok((name, value)) => set_xattr(&path, name, value, follow),
+            Err(name) => match get_xattr(&path, name, follow) {
+                Ok(None) => {
+                    outcome.fail("setfattr", &format!("{file}: {name}: No such attribute"));
+                    continue;
+                }
+                Ok(Some(_)) => remove_xattr(&path, OsStr::new(name), follow),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            outcome.fail("setfattr", &format!("{file}: {}", hal_message(&e)));
+        }
+    }
+    outcome
+}
+
+/// A value the way getfattr prints it
+fn encode(value: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Hex => format!("0x{}", hex::encode(value)),
+        Encoding::Base64 => format!(
+            "0s{}",
+            base64::engine::general_purpose::STANDARD.encode(value)
+        ),
+        Encoding::Text => {
+            let mut text = String::from("\"");
+            for &byte in value {
+                match byte {
+                    b'"' | b'\\' => {
+                        text.push('\\');
+                        text.push(byte as char);
+                    }
+                    0x20..=0x7e => text.push(byte as char),
+                    _ => text.push_str(&format!("\\{byte:03o}")),
+                }
+            }
+            text.push('"');
+            text
+        }
+    }
+}
+
+/// A value the way `stat --full` shows it: quoted when it is text, in hex
+/// otherwise
+pub(crate) fn display_value(value: &[u8]) -> String {
+    let text = value
+        .iter()
+        .all(|&byte| (0x20..0x7f).contains(&byte) || byte == b'\t' || byte == b'\n');
+    encode(value, if text { Encoding::Text } else { Encoding::Hex })
+}
+
+/// A value given to setfattr: hex after `0x`, base64 after `0s`, escaped
+/// text in double quotes, or the text itself
+fn decode(value: &str) -> Result<Vec<u8>, String> {
+    let invalid = || format!("{value}: invalid value");
+    if let Some(digits) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return hex::decode(digits).map_err(|_| invalid());
+    }
+    if let Some(encoded) = value
+        .strip_prefix("0s")
+        .or_else(|| value.strip_prefix("0S"))
+    {
+        return base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|_| invalid());
+    }
+    let Some(quoted) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return Ok(value.as_bytes().to_vec());
+    };
+    let mut bytes = Vec::new();
+    let mut chars = quoted.bytes().peekable();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        // Up to three octal digits, or the escaped character itself
+        let mut code = 0u32;
+        let mut digits = 0;
+        while let Some(digit) = chars
+            .peek()
+            .filter(|d| (b'0'..=b'7').contains(d) && digits < 3)
+        {
+            code = code * 8 + u32::from(digit - b'0');
+            digits += 1;
+            chars.next();
+        }
+        match digits {
+            0 => bytes.push(chars.next().ok_or_else(invalid)?),
+            _ => bytes.push(u8::try_from(code).map_err(|_| invalid())?),
+        }
+    }
+    Ok(bytes)
+}
@@ -1,61 +1,124 @@
-//! `yes` builtin - output a string repeatedly until killed.
+//! `yes` builtin - print a line over and over
 //!
-//! Usage:
-//!   yes [STRING]
+//! Syntax:
+//!   yes [STRING...]
 //!
-//! If no STRING is provided, outputs "y" repeatedly.
-//! This command runs indefinitely until interrupted (Ctrl+C).
-
-use crate::common::{BuiltinContext, BuiltinResult};
-use anyhow::Result;
-use std::io::{stdout, BufWriter, Write};
-
-/// Entry point for the yes builtin.
-pub fn yes_cli(args: &[String]) -> Result<()> {
-    let output_string = if args.is_empty() {
-        "y"
-    } else {
-        &args.join(" ")
-    };
+//! The STRINGs joined by spaces, or `y` when there are none, are printed as
+//! a line again and again until the output is closed or the shell's time
+//! limit runs out. Output streams straight to standard output in blocks of
+//! many lines rather than being collected, since it never ends by itself.
+//!
+//! The exit status is 0 when the reader closes the output, 124 when the
+//! time limit runs out and 1 on any other write error.
+
+use crate::common::{io_message, BuiltinContext, BuiltinResult};
+use nxsh_core::context::ShellContext;
+use nxsh_core::{Builtin, ExecutionResult, ShellResult};
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// The `yes` builtin command implementation
+pub struct YesCommand;
 
-    let stdout = stdout();
-    let mut writer = BufWriter::new(stdout.lock());
+impl Builtin for YesCommand {
+    fn name(&self) -> &'static str {
+        "yes"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "Print a line over and over"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the STRINGs, or 'y', as a line repeatedly until the output is closed."
+    }
+
+    fn usage(&self) -> &'static str {
+        "yes [STRING...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print a line over and over. Use 'yes | command' to answer every prompt with y."
+    }
 
-    loop {
-        writeln!(writer, "{output_string}")?;
-        writer.flush()?;
+    fn execute(&self, ctx: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let deadline = ctx
+            .remaining_time_budget()
+            .map(|left| Instant::now() + left);
+        let (status, error) = repeat(&block(args), &mut ctx.stdout, deadline);
+        Ok(ExecutionResult::success(status).with_error(error.unwrap_or_default().into_bytes()))
     }
 }
 
-/// Execute the yes builtin command
+/// Run yes for the legacy dispatcher, writing straight to the process's
+/// standard output and error
 pub fn execute(args: &[String], _context: &BuiltinContext) -> BuiltinResult<i32> {
-    let output_string = if args.is_empty() {
-        "y".to_string()
-    } else {
-        args.join(" ")
-    };
+    let (status, error) = repeat(&block(args), &mut io::stdout().lock(), None);
+    if let Some(message) = error {
+        io::stderr().write_all(message.as_bytes())?;
+    }
+    Ok(status)
+}
 
-    loop {
-        println!("{output_string}");
+/// The line to print, copied enough times to fill about 8 KiB so each write
+/// carries many lines
+fn block(args: &[String]) -> Vec<u8> {
+    let mut line = match args {
+        [] => "y".to_string(),
+        _ => args.join(" "),
+    };
+    line.push('\n');
+    line.repeat((8192 / line.len()).max(1)).into_bytes()
+}
 
-        // Flush stdout to ensure immediate output
-        if stdout().flush().is_err() {
-            break;
+/// Write BLOCK to OUT until writing fails or DEADLINE passes, returning the
+/// exit status and any error message
+fn repeat(block: &[u8], out: &mut dyn Write, deadline: Option<Instant>) -> (i32, Option<String>) {
+    while !deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        if let Err(e) = out.write_all(block).and_then(|()| out.flush()) {
+            return match e.kind() {
+                io::ErrorKind::BrokenPipe => (0, None),
+                _ => (
+                    1,
+                    Some(format!("yes: standard output: {}\n", io_message(&e))),
+                ),
+            };
         }
     }
-
-    // This should never be reached in normal operation
-    // as the command runs until interrupted
-    Ok(0)
+    (124, None)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    /// The lines of a block, which must end with a newline
+    fn lines(block: &[u8]) -> impl Iterator<Item = &[u8]> {
+        block.strip_suffix(b"\n").unwrap().split(|&b| b == b'\n')
+    }
+
+    #[test]
+    fn fills_blocks_with_whole_lines() {
+        let default = block(&[]);
+        assert!(default.len() >= 8190);
+        assert!(lines(&default).all(|line| line == b"y"));
+
+        let args = vec!["no".to_string(), "way".to_string()];
+        assert!(lines(&block(&args)).all(|line| line == b"no way"));
+        let long = vec!["x".repeat(10_000)];
+        assert_eq!(block(&long).len(), 10_001);
+    }
 
     #[test]
-    fn test_yes_default() {
-        // Note: This test would run indefinitely, so we can't actually test the full functionality
-        // In a real test environment, we would need to use timeouts or signal handling
-        // Removed redundant assert!(true)
+    fn stops_when_output_fails_or_time_runs_out() {
+        let mut buffer = [0u8; 100];
+        let (status, error) = repeat(b"y\n", &mut &mut buffer[..], None);
+        assert_eq!(status, 1);
+        assert!(error.unwrap().starts_with("yes: standard output: "));
+        assert!(buffer.chunks(2).all(|line| line == b"y\n"));
+
+        let mut out = Vec::new();
+        assert_eq!(repeat(b"y\n", &mut out, Some(Instant::now())), (124, None));
+        assert!(out.is_empty());
     }
 }
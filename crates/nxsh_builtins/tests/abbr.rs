@@ -0,0 +1,37 @@
+mod common;
+use common::shell;
+use nxsh_ui::abbreviations;
+
+// The registry is shared by the whole process, so each test uses its own names
+
+#[test]
+fn added_abbreviations_expand_in_the_editor() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("abbr -a --set-cursor gcm git commit -m '\"%\"'")
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+
+    let abbrs = abbreviations::shared().read().unwrap();
+    let expanded = abbrs.expand("gcm", 3).unwrap();
+    assert_eq!(expanded.line, "git commit -m \"\"");
+    assert_eq!(expanded.cursor, 15);
+}
+
+#[test]
+fn show_rename_query_and_erase() {
+    let mut sh = shell();
+    sh.eval_program("abbr --position anywhere ll1 'ls -l'")
+        .unwrap();
+
+    let shown = sh.eval_program("abbr -s").unwrap();
+    assert!(shown
+        .stdout
+        .contains("abbr -a --position anywhere -- 'll1' 'ls -l'\n"));
+
+    assert_eq!(sh.eval_program("abbr -r ll1 ll2").unwrap().exit_code, 0);
+    assert_eq!(sh.eval_program("abbr -q ll1").unwrap().exit_code, 1);
+    assert_eq!(sh.eval_program("abbr -q ll2").unwrap().exit_code, 0);
+    assert_eq!(sh.eval_program("abbr -e ll2").unwrap().exit_code, 0);
+    assert_eq!(sh.eval_program("abbr -e ll2").unwrap().exit_code, 1);
+}
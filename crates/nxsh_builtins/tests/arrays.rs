@@ -0,0 +1,59 @@
+mod common;
+use common::shell;
+
+#[test]
+fn indexed_array_expansion() {
+    let mut sh = shell();
+    sh.eval_program("a=(one 'two words' three)").unwrap();
+    let res = sh
+        .eval_program("echo ${a[1]}\necho ${#a[@]}\necho ${a[@]}\necho $a ${a[-1]}")
+        .unwrap();
+    assert_eq!(res.stdout, "two words\n3\none two words three\none three\n");
+}
+
+#[test]
+fn element_assignment_and_append() {
+    let mut sh = shell();
+    sh.eval_program("a=(x)\na[3]=z\na+=(w)\ni=1\na[i]=y")
+        .unwrap();
+    let res = sh.eval_program("echo ${a[@]}\necho ${!a[@]}").unwrap();
+    assert_eq!(res.stdout, "x y z w\n0 1 3 4\n");
+}
+
+#[test]
+fn associative_arrays_need_declare() {
+    let mut sh = shell();
+    sh.eval_program("declare -A m\nm[apple]=red\nm+=([kiwi]=green)\nk=apple")
+        .unwrap();
+    let res = sh
+        .eval_program("echo ${m[$k]} ${m[kiwi]}\necho ${!m[@]}\necho ${#m[@]}")
+        .unwrap();
+    assert_eq!(res.stdout, "red green\napple kiwi\n2\n");
+    let res = sh.eval_program("m=(nokey)").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
+
+#[test]
+fn declare_prints_arrays() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("declare -a a=(1 \"2 3\")\ndeclare -p a")
+        .unwrap();
+    assert_eq!(res.stdout, "declare -a a=([0]=\"1\" [1]=\"2 3\")\n");
+    let res = sh
+        .eval_program("declare -A m=([k]=v)\ndeclare -a m")
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res
+        .stderr
+        .contains("cannot convert associative to indexed array"));
+}
+
+#[test]
+fn scalar_length_and_unset_element() {
+    let mut sh = shell();
+    sh.eval_program("s=hello\na=(1 2 3)").unwrap();
+    assert!(sh.context().unset_var("a[1]"));
+    let res = sh.eval_program("echo ${#s} ${a[@]}").unwrap();
+    assert_eq!(res.stdout, "5 1 3\n");
+}
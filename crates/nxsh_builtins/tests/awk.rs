@@ -0,0 +1,42 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn sums_fields_of_standard_input() {
+    let mut sh = shell_with_input("root:x:0\nalice:x:1000\nbob:x:1001\n");
+    let res = sh
+        .eval_program(
+            "awk -F: -v min=1000 '$3 >= min { n++; s += $3; print NR, toupper($1) } \
+             END { printf \"%d users, total %d\\n\", n, s }'",
+        )
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "2 ALICE\n3 BOB\n2 users, total 2001\n");
+}
+
+#[test]
+fn reads_program_and_data_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("count.awk"),
+        "{ words[$1]++ }\nEND { for (w in words) print w, words[w] }\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("data"), "b\na\nb\n").unwrap();
+    let path = dir.path().display();
+
+    let mut sh = shell_with_input("");
+    let res = sh
+        .eval_program(&format!("awk -f {path}/count.awk {path}/data"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "b 2\na 1\n");
+
+    let res = sh.eval_program("awk '{ print $1' ").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(
+        res.stderr.starts_with("awk: syntax error"),
+        "{}",
+        res.stderr
+    );
+}
@@ -0,0 +1,42 @@
+//! Shells the integration tests run commands in
+
+#![allow(dead_code)]
+
+use nxsh_core::Shell;
+use std::io::Cursor;
+use std::path::Path;
+
+/// A shell without the time limit `NXSH_TIMEOUT_MS` may set, with the
+/// builtins the interactive shell registers
+pub fn shell() -> Shell {
+    prepare(Shell::new())
+}
+
+/// [`shell`] working in `cwd`
+pub fn shell_in(cwd: &Path) -> Shell {
+    let mut sh = shell();
+    sh.context_mut().cwd = cwd.to_path_buf();
+    sh
+}
+
+/// [`shell`] running as a script does, reading `input` from standard input
+pub fn shell_with_input(input: &str) -> Shell {
+    let mut sh = shell();
+    let ctx = sh.context_mut();
+    ctx.interactive = false;
+    ctx.stdin = Box::new(Cursor::new(input.as_bytes().to_vec()));
+    sh
+}
+
+/// Clear the time limit of `sh` and register the builtins of the
+/// interactive shell on it
+pub fn prepare(mut sh: Shell) -> Shell {
+    sh.context_mut().clear_global_timeout();
+    for builtin in nxsh_builtins::shell_builtins() {
+        sh.register_builtin(builtin);
+    }
+    for builtin in nxsh_builtins::structured_builtins() {
+        sh.register_structured_builtin(builtin);
+    }
+    sh
+}
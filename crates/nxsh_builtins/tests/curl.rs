@@ -0,0 +1,270 @@
+mod common;
+use common::shell;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// A server on a local port giving each connection the next of `replies`,
+/// with `{port}` standing for its own port, and passing on each request
+/// it read
+fn serve(replies: &[&str]) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let replies: Vec<String> = replies
+        .iter()
+        .map(|reply| reply.replace("{port}", &port.to_string()))
+        .collect();
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for reply in replies {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8_lossy(&body));
+            reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            sender.send(request).unwrap();
+        }
+    });
+    (port, requests)
+}
+
+fn ok(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[test]
+fn gets_a_page_and_its_headers() {
+    let reply = ok("hello\n");
+    let (port, requests) = serve(&[&reply, &reply]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl 127.0.0.1:{port}/page?a=1"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "hello\n");
+    let request = requests.recv().unwrap();
+    assert!(
+        request.starts_with("GET /page?a=1 HTTP/1.1\r\n"),
+        "{request}"
+    );
+    assert!(request.contains(&format!("Host: 127.0.0.1:{port}\r\n")));
+    assert!(request.contains("Accept: */*\r\n"));
+
+    let res = sh
+        .eval_program(&format!("curl -i -H 'X-Test: yes' 127.0.0.1:{port}"))
+        .unwrap();
+    assert_eq!(
+        res.stdout,
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nhello\n"
+    );
+    assert!(requests.recv().unwrap().contains("X-Test: yes\r\n"));
+}
+
+#[test]
+fn follows_redirects_with_location() {
+    let (port, requests) = serve(&[
+        "HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n",
+        "HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n",
+        &ok("arrived"),
+    ]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl 127.0.0.1:{port}/start"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "");
+    requests.recv().unwrap();
+
+    let res = sh
+        .eval_program(&format!("curl -L -d x=1 127.0.0.1:{port}/start"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "arrived");
+    assert!(requests.recv().unwrap().starts_with("POST /start "));
+    // A 302 after a POST goes on with a GET
+    assert!(requests.recv().unwrap().starts_with("GET /next "));
+}
+
+#[test]
+fn stops_after_max_redirs() {
+    let redirect = "HTTP/1.1 301 Moved\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n";
+    let (port, _requests) = serve(&[redirect, redirect]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl -L --max-redirs 1 127.0.0.1:{port}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 47);
+    assert_eq!(res.stderr, "curl: (47) Maximum (1) redirects followed\n");
+}
+
+#[test]
+fn posts_data_forms_and_credentials() {
+    let reply = ok("");
+    let (port, requests) = serve(&[&reply, &reply, &reply]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "curl -u ann:secret -d a=1 -d b=2 127.0.0.1:{port}/form"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("POST /form HTTP/1.1\r\n"));
+    assert!(request.contains("Authorization: Basic YW5uOnNlY3JldA==\r\n"));
+    assert!(request.contains("Content-Type: application/x-www-form-urlencoded\r\n"));
+    assert!(request.ends_with("\r\n\r\na=1&b=2"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes.txt");
+    std::fs::write(&file, "some notes").unwrap();
+    let res = sh
+        .eval_program(&format!(
+            "curl -F name=ann -F upload=@{} 127.0.0.1:{port}",
+            file.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let request = requests.recv().unwrap();
+    assert!(
+        request.contains("Content-Type: multipart/form-data; boundary=------------------------")
+    );
+    assert!(request.contains("Content-Disposition: form-data; name=\"name\"\r\n\r\nann\r\n"));
+    assert!(request.contains(
+        "Content-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"\r\nContent-Type: text/plain\r\n\r\nsome notes\r\n"
+    ));
+
+    let res = sh
+        .eval_program(&format!(
+            "curl -G -d q=nxsh --oauth2-bearer tok 127.0.0.1:{port}/search"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("GET /search?q=nxsh HTTP/1.1\r\n"));
+    assert!(request.contains("Authorization: Bearer tok\r\n"));
+}
+
+#[test]
+fn sends_and_reads_json() {
+    let body = r#"{"id":7,"tags":["a","b"]}"#;
+    let reply = format!(
+        "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let (port, requests) = serve(&[&reply, &reply]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            r#"curl --json '{{"name":"nxsh"}}' 127.0.0.1:{port}/items"#
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "{\n  \"id\": 7,\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ]\n}\n"
+    );
+    let request = requests.recv().unwrap();
+    assert!(request.contains("Content-Type: application/json\r\n"));
+    assert!(request.contains("Accept: application/json\r\n"));
+    assert!(request.ends_with("\r\n\r\n{\"name\":\"nxsh\"}"));
+
+    let res = sh
+        .eval_program(&format!(
+            "curl --json '{{}}' 127.0.0.1:{port}/items | get tags.1"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "b\n");
+}
+
+#[test]
+fn retries_transient_failures() {
+    let (port, requests) = serve(&[
+        "HTTP/1.1 503 Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n",
+        &ok("ready"),
+    ]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl --retry 2 127.0.0.1:{port}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "ready");
+    assert_eq!(
+        res.stderr,
+        "Warning: Problem : HTTP error. Will retry in 0 seconds. 2 retries left.\n"
+    );
+    assert_eq!(requests.iter().take(2).count(), 2);
+}
+
+#[test]
+fn fails_on_http_errors_with_fail() {
+    let (port, _requests) = serve(&["HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\ngone"]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl -f 127.0.0.1:{port}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 22);
+    assert_eq!(res.stdout, "");
+    assert_eq!(
+        res.stderr,
+        "curl: (22) The requested URL returned error: 404\n"
+    );
+}
+
+#[test]
+fn goes_through_a_proxy() {
+    let (port, requests) = serve(&[&ok("proxied")]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("curl -x 127.0.0.1:{port} http://example.test/a"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "proxied");
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("GET http://example.test/a HTTP/1.1\r\n"));
+    assert!(request.contains("Host: example.test\r\n"));
+}
+
+#[test]
+fn rejects_bad_options() {
+    let mut sh = shell();
+    let res = sh.eval_program("curl").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "curl: no URL specified\n");
+
+    let res = sh.eval_program("curl --retry many host").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "curl: option --retry: expected a proper numerical parameter\n"
+    );
+
+    let res = sh.eval_program("curl --http2 host").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "curl: option --http2: HTTP/2 is not supported\n"
+    );
+
+    let res = sh.eval_program("curl ftp://host/file").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "curl: (1) Protocol \"ftp\" not supported\n");
+}
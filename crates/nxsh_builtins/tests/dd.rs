@@ -0,0 +1,27 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn copies_between_files_and_streams() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    std::fs::write(root.join("data"), "header:payload").unwrap();
+
+    let mut sh = shell_with_input("streamed");
+    sh.context_mut().cwd = root.to_path_buf();
+
+    let res = sh
+        .eval_program("dd if=data of=payload bs=7 skip=1 status=noxfer")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stderr, "1+0 records in\n1+0 records out\n");
+    assert_eq!(std::fs::read(root.join("payload")).unwrap(), b"payload");
+
+    let res = sh.eval_program("dd bs=1K status=none").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "streamed");
+
+    let res = sh.eval_program("dd if=data conv=bogus").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "dd: invalid conversion: 'bogus'\n");
+}
@@ -0,0 +1,104 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+use common::shell;
+use nxsh_hal::mounts::{list_block_devices, mount_containing};
+
+#[test]
+fn df_reports_the_file_system_holding_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = mount_containing(dir.path()).unwrap().expect("mounted");
+    let mut sh = shell();
+    sh.context_mut().cwd = dir.path().to_path_buf();
+
+    let res = sh.eval_program("df -T .").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let lines: Vec<&str> = res.stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", res.stdout);
+    assert!(lines[0].starts_with("Filesystem Type"));
+    assert!(lines[0].ends_with("Mounted on"));
+    let fields: Vec<&str> = lines[1].split_whitespace().collect();
+    assert_eq!(fields[1], mount.fs_type);
+    assert_eq!(
+        fields.last().copied(),
+        mount.mount_point.to_str(),
+        "{}",
+        res.stdout
+    );
+
+    let res = sh.eval_program("df . | select type mount").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        format!("{}\t{}\n", mount.fs_type, mount.mount_point.display())
+    );
+
+    let res = sh.eval_program("df missing").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "df: missing: No such file or directory\n");
+
+    let res = sh.eval_program("df -q").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "df: invalid option -- 'q'\n");
+}
+
+#[test]
+fn mount_lists_only_the_requested_types() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = mount_containing(dir.path()).unwrap().expect("mounted");
+    let mut sh = shell();
+
+    let res = sh
+        .eval_program(&format!("mount -t {}", mount.fs_type))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res
+        .stdout
+        .lines()
+        .all(|line| line.contains(&format!(" type {} (", mount.fs_type))));
+    assert!(res.stdout.contains(&format!(
+        " on {} type {} (",
+        mount.mount_point.display(),
+        mount.fs_type
+    )));
+
+    let res = sh
+        .eval_program(&format!("mount -t {} | select type", mount.fs_type))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.lines().all(|line| line == mount.fs_type));
+}
+
+#[test]
+fn lsblk_lists_the_block_devices() {
+    let devices = list_block_devices().unwrap();
+    let mut sh = shell();
+
+    let res = sh.eval_program("lsblk -a | select name").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let names: Vec<&str> = res.stdout.lines().collect();
+    let expected: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, expected);
+
+    let res = sh.eval_program("lsblk -adl").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let mut lines = res.stdout.lines();
+    let header: Vec<&str> = lines.next().unwrap().split_whitespace().collect();
+    assert_eq!(
+        header,
+        ["NAME", "MAJ:MIN", "RM", "SIZE", "RO", "TYPE", "MOUNTPOINTS"]
+    );
+    let disks: Vec<&str> = lines
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    let expected: Vec<&str> = devices
+        .iter()
+        .filter(|d| d.parent.is_none())
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(disks, expected);
+
+    let res = sh.eval_program("lsblk nosuchdisk").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "lsblk: nosuchdisk: not a block device\n");
+}
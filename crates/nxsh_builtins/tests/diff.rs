@@ -0,0 +1,40 @@
+mod common;
+use common::shell;
+
+#[test]
+fn compares_directory_trees() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+    for side in ["old", "new"] {
+        std::fs::create_dir_all(dir.path().join(side).join("sub")).unwrap();
+        std::fs::write(dir.path().join(side).join("same.txt"), "same\n").unwrap();
+        std::fs::write(dir.path().join(side).join("skip.log"), side).unwrap();
+    }
+    std::fs::write(dir.path().join("old/sub/f.txt"), "a\nb\n").unwrap();
+    std::fs::write(dir.path().join("new/sub/f.txt"), "a\nc\n").unwrap();
+    std::fs::write(dir.path().join("old/gone.txt"), "x\n").unwrap();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("diff -r -x '*.log' {root}/old {root}/new"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        format!(
+            "Only in {root}/old: gone.txt\n\
+             diff -r -x *.log {root}/old/sub/f.txt {root}/new/sub/f.txt\n\
+             2c2\n< b\n---\n> c\n"
+        )
+    );
+
+    let res = sh
+        .eval_program(&format!("diff -q {root}/old/same.txt {root}/new"))
+        .unwrap();
+    assert_eq!((res.exit_code, res.stdout.as_str()), (0, ""));
+
+    let res = sh
+        .eval_program(&format!("diff {root}/old/same.txt {root}/missing"))
+        .unwrap();
+    assert_eq!(res.exit_code, 2);
+}
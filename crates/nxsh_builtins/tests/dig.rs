@@ -0,0 +1,82 @@
+mod common;
+use common::shell;
+use std::net::UdpSocket;
+
+/// A name server on a local port answering every A query with 192.0.2.7
+/// and every other query with NXDOMAIN
+fn serve() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok((size, peer)) = socket.recv_from(&mut buffer) {
+            let mut reply = buffer[..size].to_vec();
+            let rtype = u16::from_be_bytes([reply[size - 4], reply[size - 3]]);
+            if rtype == 1 {
+                reply[2..4].copy_from_slice(&0x8580u16.to_be_bytes());
+                reply[6..8].copy_from_slice(&1u16.to_be_bytes());
+                reply.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 192, 0, 2, 7]);
+            } else {
+                reply[2..4].copy_from_slice(&0x8583u16.to_be_bytes());
+            }
+            socket.send_to(&reply, peer).unwrap();
+        }
+    });
+    port
+}
+
+#[test]
+fn queries_a_given_server() {
+    let port = serve();
+    let mut sh = shell();
+
+    let res = sh
+        .eval_program(&format!("dig @127.0.0.1 -p {port} host.example +short"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "192.0.2.7\n");
+
+    let res = sh
+        .eval_program(&format!("dig @127.0.0.1 -p {port} host.example"))
+        .unwrap();
+    assert!(res.stdout.contains("status: NOERROR"));
+    assert!(res.stdout.contains(";; flags: qr aa rd ra;"));
+    assert!(res
+        .stdout
+        .contains(";; ANSWER SECTION:\nhost.example.\t300\tIN\tA\t192.0.2.7\n"));
+
+    let res = sh
+        .eval_program(&format!("dig @127.0.0.1 -p {port} host.example MX +json"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let answer: serde_json::Value = serde_json::from_str(&res.stdout).unwrap();
+    assert_eq!(answer["status"], "NXDOMAIN");
+    assert_eq!(answer["question"][0]["type"], "MX");
+    assert_eq!(answer["answer"].as_array().unwrap().len(), 0);
+
+    let res = sh
+        .eval_program(&format!(
+            "dig @127.0.0.1 -p {port} host.example | select name ttl data"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "host.example.\t300\t192.0.2.7\n");
+}
+
+#[test]
+fn gives_up_on_a_silent_server() {
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = silent.local_addr().unwrap().port();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "dig @127.0.0.1 -p {port} +time=1 +tries=1 host.example"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 9);
+    assert!(res.stderr.starts_with("dig: no answer (127.0.0.1:"));
+
+    let res = sh.eval_program("dig -t BOGUS host.example").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "dig: invalid type 'BOGUS'\n");
+}
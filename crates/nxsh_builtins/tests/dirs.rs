@@ -0,0 +1,60 @@
+mod common;
+use common::{prepare, shell_in};
+use nxsh_core::Shell;
+
+// One test, as the builtins move the process directory along with the shell's
+#[test]
+fn pushd_popd_and_cd_share_the_stack() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("a")).unwrap();
+    std::fs::create_dir(root.join("b")).unwrap();
+    let r = root.display();
+    let mut sh = shell_in(&root);
+
+    let res = sh.eval_program("pushd a").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh.eval_program("pushd ../b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(sh.context().cwd, root.join("b"));
+
+    let res = sh.eval_program("dirs -l -v").unwrap();
+    assert_eq!(res.stdout, format!(" 0  {r}/b\n 1  {r}/a\n 2  {r}\n"));
+
+    // Subshells work on a copy
+    sh.eval_program("(pushd +2)").unwrap();
+    let res = sh.eval_program("dirs -l").unwrap();
+    assert_eq!(res.stdout, format!("{r}/b {r}/a {r}\n"));
+
+    let res = sh.eval_program("popd").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(sh.context().cwd, root.join("a"));
+
+    let res = sh.eval_program("cd -").unwrap();
+    assert_eq!(res.stdout, format!("{r}/b\n"));
+    assert_eq!(sh.context().get_var("OLDPWD"), Some(format!("{r}/a")));
+
+    // The stack outlives the shell it was built in
+    let mut sh = prepare(Shell::from_state(sh.into_state()));
+    let res = sh.eval_program("dirs -l +1").unwrap();
+    assert_eq!(res.stdout, format!("{r}\n"));
+
+    let res = sh.eval_program("popd +9").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "popd: +9: directory stack index out of range\n");
+    let res = sh.eval_program("pushd nope").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "pushd: nope: No such file or directory\n");
+    let res = sh.eval_program("dirs -x").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "dirs: -x: invalid option\ndirs: usage: dirs [-clpv] [+N | -N]\n"
+    );
+
+    sh.eval_program(&format!("CDPATH=:{r}")).unwrap();
+    sh.eval_program("cd /").unwrap();
+    let res = sh.eval_program("cd a").unwrap();
+    assert_eq!(res.stdout, format!("{r}/a\n"));
+    assert_eq!(sh.context().cwd, root.join("a"));
+}
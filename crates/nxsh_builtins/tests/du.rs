@@ -0,0 +1,94 @@
+#![cfg(unix)]
+
+mod common;
+use common::shell_in;
+use std::fs;
+use std::path::Path;
+
+fn len(path: &Path) -> u64 {
+    fs::symlink_metadata(path).unwrap().len()
+}
+
+#[test]
+fn sums_directories_after_their_contents() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("a/f1"), vec![0u8; 10_000]).unwrap();
+    fs::write(root.join("a/b/f2"), vec![0u8; 5_000]).unwrap();
+    fs::hard_link(root.join("a/b/f2"), root.join("a/b/f2link")).unwrap();
+    fs::write(root.join("c"), vec![0u8; 3_000]).unwrap();
+
+    // The hard link is counted once
+    let b = len(&root.join("a/b")) + 5_000;
+    let a = len(&root.join("a")) + 10_000 + b;
+    let all = len(root) + a + 3_000;
+    let mut sh = shell_in(root);
+
+    let res = sh.eval_program("du -b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{b}\t./a/b\n{a}\t./a\n{all}\t.\n"));
+
+    let res = sh.eval_program("du -ab -d 1").unwrap();
+    assert_eq!(res.stdout, format!("{a}\t./a\n3000\t./c\n{all}\t.\n"));
+
+    let res = sh.eval_program("du -sbc a c").unwrap();
+    assert_eq!(
+        res.stdout,
+        format!("{a}\ta\n3000\tc\n{}\ttotal\n", a + 3_000)
+    );
+
+    let res = sh.eval_program("du -ab -d 1 -t -4000").unwrap();
+    assert_eq!(res.stdout, "3000\t./c\n");
+
+    let res = sh
+        .eval_program("du -sb a | select path size files")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("a\t{a}\t2\n"));
+
+    let res = sh.eval_program("du -s missing c").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "du: cannot access 'missing': No such file or directory\n"
+    );
+    assert!(res.stdout.ends_with("\tc\n"));
+}
+
+#[test]
+fn scans_a_wide_tree_in_parallel() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let mut expected = len(root);
+    for i in 0..40 {
+        let sub = root.join(format!("d{i:02}"));
+        fs::create_dir(&sub).unwrap();
+        for j in 0..5 {
+            let deeper = sub.join(format!("e{j}"));
+            fs::create_dir(&deeper).unwrap();
+            fs::write(deeper.join("file"), vec![0u8; i * 100 + j]).unwrap();
+            expected += len(&deeper) + (i * 100 + j) as u64;
+        }
+        expected += len(&sub);
+    }
+
+    let mut sh = shell_in(root);
+    let res = sh.eval_program("du -sb").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{expected}\t.\n"));
+
+    // Directories come out in name order however the threads finished
+    let res = sh.eval_program("du -b -d 1").unwrap();
+    let names: Vec<&str> = res
+        .stdout
+        .lines()
+        .map(|line| line.split('\t').nth(1).unwrap())
+        .collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    sorted.rotate_left(1);
+    assert_eq!(names.len(), 41);
+    assert_eq!(names.last(), Some(&"."));
+    assert_eq!(names, sorted);
+}
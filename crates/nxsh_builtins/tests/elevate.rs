@@ -0,0 +1,35 @@
+mod common;
+use common::shell;
+
+#[test]
+fn reports_usage_and_missing_commands() {
+    let mut sh = shell();
+    let res = sh.eval_program("elevate").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res
+        .stderr
+        .starts_with("elevate: a command is required\nelevate: usage: elevate "));
+
+    let res = sh.eval_program("runas -b nobody ls").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res.stderr.starts_with("runas: nobody: unknown backend\n"));
+
+    let res = sh.eval_program("elevate nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert_eq!(
+        res.stderr,
+        "elevate: nxsh-no-such-command: command not found\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_directly_when_already_elevated() {
+    if !nxsh_hal::privilege::is_elevated() {
+        return;
+    }
+    let mut sh = shell();
+    let res = sh.eval_program("elevate echo ready").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "ready\n");
+}
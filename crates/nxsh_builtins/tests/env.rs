@@ -0,0 +1,98 @@
+mod common;
+use common::shell_in;
+
+#[test]
+fn prints_a_modified_copy_of_the_exports() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    sh.eval_program("NXSH_ENV_GREETING=hello").unwrap();
+
+    let res = sh.eval_program("env").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res
+        .stdout
+        .lines()
+        .any(|line| line == "NXSH_ENV_GREETING=hello"));
+
+    let res = sh
+        .eval_program("env -u NXSH_ENV_GREETING NXSH_ENV_OTHER=1")
+        .unwrap();
+    assert!(!res.stdout.contains("NXSH_ENV_GREETING="));
+    assert!(res.stdout.lines().any(|line| line == "NXSH_ENV_OTHER=1"));
+
+    let res = sh.eval_program("env -i B=2 A=x=1").unwrap();
+    assert_eq!(res.stdout, "A=x=1\nB=2\n");
+    let res = sh.eval_program("env -0 - A=1 B=2").unwrap();
+    assert_eq!(res.stdout, "A=1\0B=2\0");
+
+    // None of that touched the shell's own variables
+    let ctx = sh.context();
+    assert_eq!(ctx.get_var("NXSH_ENV_GREETING").as_deref(), Some("hello"));
+    assert_eq!(ctx.get_var("NXSH_ENV_OTHER"), None);
+}
+
+#[test]
+fn reports_usage_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+
+    let res = sh.eval_program("env -x").unwrap();
+    assert_eq!(res.exit_code, 125);
+    assert_eq!(
+        res.stderr,
+        "env: invalid option -- 'x'\nTry 'env --help' for more information.\n"
+    );
+
+    let res = sh.eval_program("env -C sub").unwrap();
+    assert_eq!(res.exit_code, 125);
+    assert!(res
+        .stderr
+        .starts_with("env: must specify command with --chdir (-C)\n"));
+
+    let res = sh.eval_program("env -C missing true").unwrap();
+    assert_eq!(res.exit_code, 125);
+    assert_eq!(
+        res.stderr,
+        "env: cannot change directory to 'missing': No such file or directory\n"
+    );
+
+    let res = sh
+        .eval_program("env -i PATH=/nonexistent nxsh-no-such-command")
+        .unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert_eq!(
+        res.stderr,
+        "env: 'nxsh-no-such-command': No such file or directory\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_commands_in_the_new_environment() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    let mut sh = shell_in(dir.path());
+    sh.eval_program("NXSH_ENV_GREETING=hello").unwrap();
+
+    let res = sh.eval_program("env printenv NXSH_ENV_GREETING").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "hello\n");
+
+    // Without PATH the command is looked for in the default directories
+    let res = sh.eval_program("env -i FOO=bar printenv").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "FOO=bar\n");
+
+    let res = sh
+        .eval_program("env -u NXSH_ENV_GREETING printenv NXSH_ENV_GREETING")
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+
+    let res = sh.eval_program("env -C sub pwd").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let sub = dir.path().join("sub").canonicalize().unwrap();
+    assert_eq!(res.stdout.trim_end(), sub.display().to_string());
+
+    let res = sh.eval_program("env sh -c 'exit 3'").unwrap();
+    assert_eq!(res.exit_code, 3);
+}
@@ -0,0 +1,28 @@
+mod common;
+use common::shell_in;
+
+#[test]
+fn identifies_files_by_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    // A PNG header under a misleading name
+    std::fs::write(
+        root.join("picture.txt"),
+        b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x02\0\0\0\x03",
+    )
+    .unwrap();
+    std::fs::write(root.join("tool"), "#!/usr/bin/env python3\n").unwrap();
+
+    let mut sh = shell_in(root);
+
+    let res = sh.eval_program("file picture.txt tool").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "picture.txt: PNG image data, 2 x 3\n\
+         tool:        Python script, ASCII text executable\n"
+    );
+
+    let res = sh.eval_program("file -b --mime-type picture.txt").unwrap();
+    assert_eq!(res.stdout, "image/png\n");
+}
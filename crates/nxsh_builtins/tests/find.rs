@@ -0,0 +1,33 @@
+mod common;
+use common::shell;
+
+#[test]
+fn finds_files_by_expression() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("docs/old")).unwrap();
+    std::fs::write(dir.path().join("docs/guide.md"), "guide\n").unwrap();
+    std::fs::write(dir.path().join("docs/old/notes.md"), "notes\n").unwrap();
+    std::fs::write(dir.path().join("docs/logo.png"), [0u8; 4]).unwrap();
+    let path = dir.path().display();
+
+    let mut sh = shell();
+
+    let res = sh
+        .eval_program(&format!(
+            "find {path}/docs -name old -prune -o -type f -name '*.md' -print"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{path}/docs/guide.md\n"));
+
+    let res = sh
+        .eval_program(&format!(
+            "find {path}/docs -mindepth 1 -not -type d -printf '%P %s\\n'"
+        ))
+        .unwrap();
+    assert_eq!(res.stdout, "guide.md 6\nlogo.png 4\nold/notes.md 6\n");
+
+    let res = sh.eval_program("find . -frobnicate").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "find: unknown predicate `-frobnicate'\n");
+}
@@ -0,0 +1,53 @@
+//! Function call frames: `local`, `return`, `$?`, `$@` and `FUNCNAME`
+mod common;
+use common::shell;
+
+#[test]
+fn locals_shadow_and_restore_with_dynamic_scope() {
+    let mut sh = shell();
+    sh.eval_program("x=global").unwrap();
+    sh.eval_program("function show() { echo $x; }").unwrap();
+    sh.eval_program("function outer() { local x=outer; show; x=changed; show; }")
+        .unwrap();
+
+    let res = sh.eval_program("outer\necho $x").unwrap();
+    assert_eq!(res.stdout, "outer\nchanged\nglobal\n");
+
+    let res = sh.eval_program("local y=1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("can only be used in a function"));
+}
+
+#[test]
+fn declare_inside_a_function_is_local_unless_global() {
+    let mut sh = shell();
+    sh.eval_program("function f() { declare a=1; declare -g b=2; }")
+        .unwrap();
+
+    let res = sh.eval_program("f\necho [$a] [$b]").unwrap();
+    assert_eq!(res.stdout, "[] [2]\n");
+}
+
+#[test]
+fn return_leaves_only_the_current_call() {
+    let mut sh = shell();
+    sh.eval_program("function inner() { return 3; echo unreachable; }")
+        .unwrap();
+    sh.eval_program("function outer() { inner; echo inner said $?; return; }")
+        .unwrap();
+
+    let res = sh.eval_program("outer\necho outer said $?").unwrap();
+    assert_eq!(res.stdout, "inner said 3\nouter said 0\n");
+}
+
+#[test]
+fn positionals_and_funcname_follow_the_call_stack() {
+    let mut sh = shell();
+    sh.eval_program("function inner() { echo ${FUNCNAME[0]} ${FUNCNAME[1]} $#; }")
+        .unwrap();
+    sh.eval_program("function outer() { inner a b c; echo $FUNCNAME $1; }")
+        .unwrap();
+
+    let res = sh.eval_program("outer first\necho [$FUNCNAME]").unwrap();
+    assert_eq!(res.stdout, "inner outer 3\nouter first\n[]\n");
+}
@@ -0,0 +1,54 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn searches_standard_input() {
+    let mut sh = shell_with_input("alpha\nbeta\ngamma\n");
+    let res = sh.eval_program("grep -n 'a$'").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "1:alpha\n2:beta\n3:gamma\n");
+
+    let res = sh.eval_program("grep delta").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stdout, "");
+}
+
+#[test]
+fn walks_directories_recursively() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src/skip")).unwrap();
+    std::fs::write(dir.path().join("a.txt"), "needle one\nhay\n").unwrap();
+    std::fs::write(dir.path().join("src/b.rs"), "hay\nNEEDLE two\n").unwrap();
+    std::fs::write(dir.path().join("src/skip/c.rs"), "needle three\n").unwrap();
+    std::fs::write(dir.path().join("blob.bin"), b"needle\0\x01").unwrap();
+    let path = dir.path().display();
+
+    let mut sh = shell_with_input("");
+    let res = sh
+        .eval_program(&format!("grep -rin --exclude-dir=skip needle {path}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        format!(
+            "{path}/a.txt:1:needle one\n\
+             Binary file {path}/blob.bin matches\n\
+             {path}/src/b.rs:2:NEEDLE two\n"
+        )
+    );
+
+    let res = sh
+        .eval_program(&format!("grep -rl --include='*.rs' needle {path}"))
+        .unwrap();
+    assert_eq!(res.stdout, format!("{path}/src/skip/c.rs\n"));
+
+    let res = sh
+        .eval_program(&format!("grep needle {path}/missing {path}/a.txt"))
+        .unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stdout, format!("{path}/a.txt:needle one\n"));
+    assert_eq!(
+        res.stderr,
+        format!("grep: {path}/missing: No such file or directory\n")
+    );
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "compression-gzip")]
+
+mod common;
+use common::shell;
+
+#[test]
+fn searches_and_prints_compressed_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+    std::fs::write(dir.path().join("app.log"), "start\nneedle found\nstop\n").unwrap();
+
+    let mut sh = shell();
+    let res = sh.eval_program(&format!("gzip -k {root}/app.log")).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(dir.path().join("app.log").exists());
+
+    let res = sh.eval_program(&format!("zcat {root}/app.log.gz")).unwrap();
+    assert_eq!(res.stdout, "start\nneedle found\nstop\n");
+
+    let res = sh
+        .eval_program(&format!("grep -n needle {root}/app.log.gz"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "2:needle found\n");
+
+    let res = sh
+        .eval_program(&format!("grep -c --no-decompress needle {root}/app.log.gz"))
+        .unwrap();
+    assert_eq!(res.stdout, "0\n");
+}
@@ -0,0 +1,38 @@
+mod common;
+use common::shell;
+
+#[test]
+fn lists_and_verifies_digests() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+    std::fs::write(dir.path().join("one"), "first\n").unwrap();
+    std::fs::write(dir.path().join("two"), "second\n").unwrap();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("sha256sum {root}/one {root}/two"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    std::fs::write(dir.path().join("SUMS"), &res.stdout).unwrap();
+
+    let res = sh
+        .eval_program(&format!("sha256sum -c {root}/SUMS"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{root}/one: OK\n{root}/two: OK\n"));
+
+    std::fs::write(dir.path().join("two"), "tampered\n").unwrap();
+    let res = sh
+        .eval_program(&format!("sha256sum -c --quiet {root}/SUMS"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stdout, format!("{root}/two: FAILED\n"));
+    assert!(res.stderr.contains("1 computed checksum did NOT match"));
+
+    let res = sh
+        .eval_program(&format!("b2sum -l 64 --tag {root}/one"))
+        .unwrap();
+    assert!(res
+        .stdout
+        .starts_with(&format!("BLAKE2b-64 ({root}/one) = ")));
+}
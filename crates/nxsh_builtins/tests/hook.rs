@@ -0,0 +1,94 @@
+mod common;
+use common::{prepare, shell_in};
+use nxsh_core::{ExecutionResult, Shell};
+
+/// Run `line` as the interactive loop does, which fires the hooks
+fn run(sh: &mut Shell, line: &str) -> ExecutionResult {
+    let ast = nxsh_parser::Parser::new().parse(line).unwrap();
+    sh.eval_ast(&ast).unwrap()
+}
+
+// One test, as `cd` moves the process directory along with the shell's
+#[test]
+fn hooks_run_on_events_and_can_be_listed_and_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("project")).unwrap();
+    std::fs::write(root.join("project/.envrc"), "PROJECT=alpha\n").unwrap();
+    let r = root.display();
+    let mut sh = shell_in(&root);
+
+    // direnv in a line
+    let envrc = "if [ -f .envrc ]; then source .envrc; fi";
+    let res = run(&mut sh, &format!("hook add cwd_changed '{envrc}'"));
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let announce = "echo \"$HOOK_OLDPWD -> $HOOK_PWD\"";
+    run(&mut sh, &format!("hook add cwd_changed '{announce}'"));
+    let res = run(&mut sh, "cd project");
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{r} -> {r}/project\n"));
+    assert_eq!(sh.context().get_var("PROJECT"), Some("alpha".to_string()));
+    assert_eq!(sh.context().get_var("HOOK_PWD"), None);
+    // Only a command line that changes directory fires it
+    let res = run(&mut sh, "echo hi");
+    assert_eq!(res.stdout, "hi\n");
+
+    // Hooks run around each command line and keep `$?`
+    run(&mut sh, "hook add post_command false");
+    run(&mut sh, "true");
+    let res = run(&mut sh, "echo $?");
+    assert_eq!(res.stdout, "0\n");
+    run(&mut sh, "hook add pre_command 'echo \"> $HOOK_COMMAND\"'");
+    let res = run(&mut sh, "echo hi");
+    assert_eq!(res.stdout, "> echo hi\nhi\n");
+    run(&mut sh, "hook remove pre_command");
+
+    // Adding a hook again keeps one
+    run(&mut sh, &format!("hook add cwd_changed '{envrc}'"));
+    let res = run(&mut sh, "hook list cwd_changed");
+    assert_eq!(
+        res.stdout,
+        "hook add cwd_changed 'if [ -f .envrc ]; then source .envrc; fi'\n\
+         hook add cwd_changed 'echo \"$HOOK_OLDPWD -> $HOOK_PWD\"'\n"
+    );
+
+    let res = run(&mut sh, &format!("hook remove cwd_changed '{announce}'"));
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = run(&mut sh, &format!("hook remove cwd_changed '{announce}'"));
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "hook: cwd_changed: no such hook\n");
+    let res = run(&mut sh, "hook");
+    assert_eq!(
+        res.stdout,
+        "hook add post_command 'false'\n\
+         hook add cwd_changed 'if [ -f .envrc ]; then source .envrc; fi'\n"
+    );
+
+    // The hooks outlive the shell they were added in
+    run(&mut sh, "cd ..");
+    run(&mut sh, "PROJECT=beta");
+    let mut sh = prepare(Shell::from_state(sh.into_state()));
+    let res = run(&mut sh, "cd project");
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(sh.context().get_var("PROJECT"), Some("alpha".to_string()));
+}
+
+#[test]
+fn rejects_unknown_events_and_subcommands() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    let res = sh.eval_program("hook add on_lunch 'echo hungry'").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "hook: on_lunch: no such event\n");
+    let res = sh.eval_program("hook add prompt_render").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "hook: prompt_render: missing command\n");
+    let res = sh.eval_program("hook fire").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "hook: fire: invalid subcommand\n");
+    let res = sh.eval_program("hook events").unwrap();
+    assert_eq!(
+        res.stdout,
+        "pre_command\npost_command\ncwd_changed\nprompt_render\njob_finished\n"
+    );
+}
@@ -0,0 +1,136 @@
+mod common;
+use common::shell_with_input;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A port nothing listens on, as far as a test can tell
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn sends_input_and_prints_the_reply() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = String::new();
+        stream.read_to_string(&mut request).unwrap();
+        stream
+            .write_all(format!("got {request}").as_bytes())
+            .unwrap();
+    });
+
+    let mut sh = shell_with_input("hello\n");
+    let res = sh.eval_program(&format!("nc 127.0.0.1 {port}")).unwrap();
+    server.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "got hello\n");
+}
+
+#[test]
+fn listens_for_one_client() {
+    let port = free_port();
+    let client = thread::spawn(move || {
+        let start = Instant::now();
+        let mut stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)) {
+                Ok(stream) => break stream,
+                Err(_) if start.elapsed() < Duration::from_secs(10) => {
+                    thread::sleep(Duration::from_millis(20))
+                }
+                Err(e) => panic!("{e}"),
+            }
+        };
+        stream.write_all(b"from client").unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        reply
+    });
+
+    let mut sh = shell_with_input("from server");
+    let res = sh
+        .eval_program(&format!("nc -l -v -p {port} 127.0.0.1"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "from client");
+    assert!(res.stderr.starts_with(&format!(
+        "Listening on 127.0.0.1 {port}\nConnection received on 127.0.0.1 "
+    )));
+    assert_eq!(client.join().unwrap(), "from server");
+}
+
+#[test]
+fn exchanges_datagrams_until_idle() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    let echo = thread::spawn(move || {
+        let mut buf = [0; 64];
+        let (n, peer) = server.recv_from(&mut buf).unwrap();
+        let reply = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+        server.send_to(reply.as_bytes(), peer).unwrap();
+    });
+
+    let mut sh = shell_with_input("ping");
+    let res = sh
+        .eval_program(&format!("nc -u -w 1 127.0.0.1 {port}"))
+        .unwrap();
+    echo.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "PING");
+}
+
+#[test]
+fn scans_open_and_closed_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let closed = free_port();
+
+    let mut sh = shell_with_input("");
+    let res = sh.eval_program(&format!("nc -z 127.0.0.1 {open}")).unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "");
+    assert_eq!(res.stderr, "");
+
+    let res = sh
+        .eval_program(&format!("nc -zv -w 2 127.0.0.1 {closed} {open}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    let lines: Vec<&str> = res.stderr.lines().collect();
+    assert!(
+        lines[0].starts_with(&format!(
+            "nc: connect to 127.0.0.1 port {closed} (tcp) failed: "
+        )),
+        "{}",
+        lines[0]
+    );
+    assert_eq!(
+        lines[1],
+        format!("Connection to 127.0.0.1 {open} port [tcp/*] succeeded!")
+    );
+
+    let res = sh
+        .eval_program(&format!("nc -z 127.0.0.1 {closed}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "");
+}
+
+#[test]
+fn rejects_bad_options() {
+    let mut sh = shell_with_input("");
+    let res = sh.eval_program("nc 127.0.0.1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "nc: missing port number\n");
+
+    let res = sh.eval_program("nc -n host.invalid 80").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "nc: host.invalid: Name or service not known\n");
+}
@@ -0,0 +1,42 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+use common::shell;
+use std::net::TcpListener;
+
+#[test]
+fn lists_a_listening_socket_as_text_and_rows() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local = listener.local_addr().unwrap();
+    let port = local.port();
+
+    let mut sh = shell();
+
+    let res = sh.eval_program("netstat -tlp").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.starts_with("Proto "));
+    let line = res
+        .stdout
+        .lines()
+        .find(|line| line.split_whitespace().nth(1) == Some(&local.to_string()))
+        .expect("listener listed");
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(fields[0], "tcp");
+    assert_eq!(fields[3], "LISTEN");
+    assert!(fields[4].starts_with(&format!("{}/", std::process::id())));
+
+    let res = sh.eval_program("netstat -tu").unwrap();
+    assert!(!res.stdout.contains(&local.to_string()));
+
+    let res = sh
+        .eval_program(&format!(
+            "netstat -l | where local_port -eq {port} | select proto state pid"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("tcp\tLISTEN\t{}\n", std::process::id()));
+
+    let res = sh.eval_program("netstat -x").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "netstat: invalid option -- 'x'\n");
+}
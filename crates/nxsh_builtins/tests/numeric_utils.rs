@@ -0,0 +1,44 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn prints_sequences_and_factors() {
+    let mut sh = shell_with_input("");
+    let res = sh.eval_program("seq -s ' ' 0 0.5 2").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "0.0 0.5 1.0 1.5 2.0\n");
+
+    let res = sh.eval_program("seq -w 9 11").unwrap();
+    assert_eq!(res.stdout, "09\n10\n11\n");
+
+    let res = sh.eval_program("factor 84 97").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "84: 2 2 3 7\n97: 97\n");
+
+    let mut sh = shell_with_input("12\n");
+    let res = sh.eval_program("factor").unwrap();
+    assert_eq!(res.stdout, "12: 2 2 3\n");
+}
+
+#[test]
+fn shuffles_reproducibly() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+    let seed: Vec<u8> = (0..=255u8).rev().cycle().take(2048).collect();
+    std::fs::write(dir.path().join("seed"), seed).unwrap();
+
+    let mut sh = shell_with_input("");
+    let command = format!("shuf --random-source={root}/seed -i 1-20");
+    let first = sh.eval_program(&command).unwrap();
+    assert_eq!(first.exit_code, 0, "{}", first.stderr);
+    let second = sh.eval_program(&command).unwrap();
+    assert_eq!(first.stdout, second.stdout);
+
+    let mut numbers: Vec<u32> = first.stdout.lines().map(|n| n.parse().unwrap()).collect();
+    numbers.sort_unstable();
+    assert_eq!(numbers, (1..=20).collect::<Vec<_>>());
+
+    let mut sh = shell_with_input("a\nb\nc\n");
+    let res = sh.eval_program("shuf -n 2").unwrap();
+    assert_eq!(res.stdout.lines().count(), 2);
+}
@@ -0,0 +1,49 @@
+mod common;
+use common::shell_in;
+
+#[test]
+fn splits_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    let res = sh.eval_program("basename /src/lib.rs .rs").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "lib\n");
+
+    let res = sh.eval_program("dirname /src/lib.rs name").unwrap();
+    assert_eq!(res.stdout, "/src\n.\n");
+}
+
+#[test]
+fn resolves_and_creates_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    let mut sh = shell_in(&root);
+
+    let res = sh.eval_program("mktemp -d -p . work.XXXXXX").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let created = std::path::Path::new(res.stdout.trim_end());
+    let name = created.file_name().unwrap().to_string_lossy().into_owned();
+    let work = root.join(&name);
+    assert!(work.is_dir());
+
+    let res = sh
+        .eval_program(&format!("realpath ./{name}/../{name}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{}\n", work.display()));
+
+    let res = sh
+        .eval_program(&format!("realpath -m --relative-to={name} out/bin"))
+        .unwrap();
+    assert_eq!(
+        res.stdout,
+        format!(
+            "..{}out{}bin\n",
+            std::path::MAIN_SEPARATOR,
+            std::path::MAIN_SEPARATOR
+        )
+    );
+
+    let res = sh.eval_program("readlink -f missing").unwrap();
+    assert_eq!(res.stdout, format!("{}\n", root.join("missing").display()));
+}
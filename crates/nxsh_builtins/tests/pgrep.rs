@@ -0,0 +1,27 @@
+#![cfg(unix)]
+
+mod common;
+use common::shell;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+
+#[test]
+fn finds_and_signals_by_command_line() {
+    let mut child = Command::new("sleep").arg("4.321").spawn().unwrap();
+    let pid = child.id();
+
+    let mut sh = shell();
+    sh.context_mut().interactive = false;
+
+    let res = sh.eval_program("pgrep -f 'sleep 4.321'").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, format!("{pid}\n"));
+
+    let res = sh.eval_program("pkill -KILL -f 'sleep 4.321'").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(child.wait().unwrap().signal(), Some(9));
+
+    let res = sh.eval_program("pgrep -x no-such-process-name").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stdout, "");
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+mod common;
+use common::shell;
+
+#[test]
+fn pings_and_traces_the_loopback_address() {
+    let mut sh = shell();
+    let res = sh.eval_program("ping -c 2 -i 0.2 -W 1 127.0.0.1").unwrap();
+    // Without a datagram ICMP socket or the right to a raw one there is
+    // nothing to send the requests on
+    if res.exit_code == 2 && res.stderr.starts_with("ping: socket: ") {
+        return;
+    }
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let lines: Vec<&str> = res.stdout.lines().collect();
+    assert_eq!(lines[0], "PING 127.0.0.1 (127.0.0.1) 56(84) bytes of data.");
+    assert!(lines[1].starts_with("64 bytes from 127.0.0.1: icmp_seq=1 "));
+    assert!(lines[2].starts_with("64 bytes from 127.0.0.1: icmp_seq=2 "));
+    assert_eq!(lines[4], "--- 127.0.0.1 ping statistics ---");
+    assert!(lines[5].starts_with("2 packets transmitted, 2 received, 0% packet loss, time "));
+    assert!(lines[6].starts_with("rtt min/avg/max/mdev = "));
+
+    let res = sh
+        .eval_program("traceroute -n -q 1 -w 1 127.0.0.1")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let lines: Vec<&str> = res.stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "traceroute to 127.0.0.1 (127.0.0.1), 30 hops max, 60 byte packets"
+    );
+    assert!(lines[1].starts_with(" 1  127.0.0.1  "), "{}", lines[1]);
+    assert_eq!(lines.len(), 2);
+}
+
+#[test]
+fn rejects_bad_options_and_hosts() {
+    let mut sh = shell();
+    let res = sh.eval_program("ping -c 0 127.0.0.1").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "ping: invalid count '0'\n");
+
+    let res = sh.eval_program("ping -c 1 host.invalid").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "ping: host.invalid: Name or service not known\n"
+    );
+
+    let res = sh.eval_program("traceroute -m 0 127.0.0.1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "traceroute: invalid max hops '0'\n");
+}
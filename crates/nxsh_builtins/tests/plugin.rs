@@ -0,0 +1,325 @@
+#![cfg(feature = "plugins")]
+
+mod common;
+use nxsh_core::{CompletionSpec, ExecutionResult, PluginCommand, Shell};
+use nxsh_plugin::consent::{Decision, PermissionGrants, GRANTS_FILE};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+
+/// Plugins are installed into one directory for the whole test process
+fn plugin_dir() -> PathBuf {
+    static SET: Once = Once::new();
+    let dir = std::env::temp_dir().join(format!("nxsh-plugin-test-{}", std::process::id()));
+    SET.call_once(|| std::env::set_var("NXSH_PLUGIN_DIR", &dir));
+    dir
+}
+
+/// Permissions are recorded in one configuration directory for the whole
+/// test process
+fn config_dir() -> PathBuf {
+    static SET: Once = Once::new();
+    let dir = std::env::temp_dir().join(format!("nxsh-plugin-config-{}", std::process::id()));
+    SET.call_once(|| std::env::set_var("NXSH_CONFIG_DIR", &dir));
+    dir
+}
+
+fn shell() -> Shell {
+    plugin_dir();
+    common::shell()
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write an empty WASM module with the manifest of `greeter` at `version`
+fn write_greeter(dir: &Path, version: &str) {
+    let manifest =
+        format!(r#"{{"name": "greeter", "version": "{version}", "commands": ["greet"]}}"#);
+    let name = b"nxsh_plugin";
+    let mut section = leb128(name.len());
+    section.extend_from_slice(name);
+    section.extend_from_slice(manifest.as_bytes());
+    let mut wasm = b"\0asm\x01\0\0\0\0".to_vec();
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    std::fs::write(dir.join("greeter.wasm"), wasm).unwrap();
+}
+
+/// Write a WASM module for plugin `waver` at `version` that exports an
+/// empty function for each of `commands`
+fn write_waver(path: &Path, version: &str, commands: &[&str]) {
+    let section = |id: u8, body: Vec<u8>| {
+        let mut section = vec![id];
+        section.extend(leb128(body.len()));
+        section.extend(body);
+        section
+    };
+    let mut exports = leb128(commands.len());
+    for command in commands {
+        exports.extend(leb128(command.len()));
+        exports.extend_from_slice(command.as_bytes());
+        exports.extend([0, 0]);
+    }
+    let manifest =
+        format!(r#"{{"name": "waver", "version": "{version}", "commands": {commands:?}}}"#);
+    let mut custom = leb128(b"nxsh_plugin".len());
+    custom.extend_from_slice(b"nxsh_plugin");
+    custom.extend_from_slice(manifest.as_bytes());
+
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    // One function type, () -> (), one function of it and its empty body
+    wasm.extend(section(1, vec![1, 0x60, 0, 0]));
+    wasm.extend(section(3, vec![1, 0]));
+    wasm.extend(section(7, exports));
+    wasm.extend(section(10, vec![1, 2, 0, 0x0b]));
+    wasm.extend(section(0, custom));
+    std::fs::write(path, wasm).unwrap();
+}
+
+#[test]
+fn installs_lists_disables_updates_and_removes() {
+    let downloads = tempfile::tempdir().unwrap();
+    write_greeter(downloads.path(), "1.0.0");
+    let mut sh = shell();
+    sh.context_mut().cwd = downloads.path().to_path_buf();
+
+    let res = sh.eval_program("plugin install greeter.wasm").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "installed greeter 1.0.0\n");
+    assert!(plugin_dir().join("greeter.wasm").exists());
+
+    let res = sh.eval_program("plugin list").unwrap();
+    assert_eq!(res.stdout, "greeter  1.0.0  enabled   unsigned\n");
+
+    // Disabling a plugin takes its commands out of the running shell
+    let registry = sh.context_mut().plugin_commands.clone();
+    registry.register(PluginCommand {
+        name: "greet".to_string(),
+        plugin: "greeter@1.0.0".to_string(),
+        synopsis: String::new(),
+        help: String::new(),
+        completion: CompletionSpec::default(),
+        handler: Arc::new(|_, _| Ok(ExecutionResult::success(0))),
+    });
+    let res = sh.eval_program("plugin disable greeter").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(!registry.contains("greet"));
+    let res = sh.eval_program("plugin list").unwrap();
+    assert_eq!(res.stdout, "greeter  1.0.0  disabled  unsigned\n");
+    let res = sh.eval_program("plugin enable greeter").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+    let res = sh.eval_program("plugin update greeter").unwrap();
+    assert_eq!(res.stdout, "greeter 1.0.0 is up to date\n");
+    write_greeter(downloads.path(), "1.1.0");
+    let res = sh.eval_program("plugin update greeter").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "updated greeter 1.0.0 -> 1.1.0\n");
+
+    let res = sh.eval_program("plugin remove greeter").unwrap();
+    assert_eq!(res.stdout, "removed greeter 1.1.0\n");
+    assert!(!plugin_dir().join("greeter.wasm").exists());
+    assert!(!plugin_dir().join(".cache/greeter").exists());
+    let res = sh.eval_program("plugin list").unwrap();
+    assert_eq!(res.stdout, "");
+
+    let res = sh.eval_program("plugin remove greeter").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "plugin: greeter is not installed\n");
+}
+
+#[test]
+fn reports_usage_errors() {
+    let mut sh = shell();
+    for command in [
+        "plugin",
+        "plugin frob",
+        "plugin list extra",
+        "plugin remove -f x",
+        "plugin list --insecure-allow-revoked",
+        "plugin keys add -t admin acme AAAA",
+        "plugin keys remove",
+        "plugin verify",
+    ] {
+        let res = sh.eval_program(command).unwrap();
+        assert_eq!(res.exit_code, 2, "{command}");
+        assert!(res.stderr.contains("plugin: usage: "), "{command}");
+    }
+}
+
+#[test]
+fn manages_the_keys_trusted_to_sign_plugins() {
+    let mut sh = shell();
+    let key = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    let fingerprint = "sha256:630dcd2966c4336691125448bbb25b4ff412a49c732db2c8abc1b8581bd710dd";
+
+    let res = sh
+        .eval_program(&format!("plugin keys add -t root acme {key}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "added acme as a root key\n");
+    let res = sh
+        .eval_program(&format!("plugin keys add acme {key}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "plugin: acme is already in the keyring\n");
+    let res = sh.eval_program("plugin keys add bad AAAA").unwrap();
+    assert_eq!(
+        res.stderr,
+        "plugin: invalid public key: expected a base64 Ed25519 key\n"
+    );
+
+    let res = sh.eval_program("plugin keys list").unwrap();
+    let listed = res
+        .stdout
+        .lines()
+        .any(|line| line.split_whitespace().eq(["acme", "root", fingerprint]));
+    assert!(listed, "{}", res.stdout);
+    let res = sh
+        .eval_program("plugin keys trust-level acme signer")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh.eval_program("plugin keys trust-level acme").unwrap();
+    assert_eq!(res.stdout, "signer\n");
+
+    let res = sh.eval_program("plugin keys remove acme").unwrap();
+    assert_eq!(res.stdout, "removed acme\n");
+    let res = sh.eval_program("plugin keys trust-level acme").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "plugin: acme: no such key\n");
+}
+
+#[test]
+fn verifies_plugins_conform() {
+    let build = tempfile::tempdir().unwrap();
+    write_waver(&build.path().join("waver.wasm"), "1.0.0", &["wave"]);
+    write_greeter(build.path(), "1.0.0");
+    let mut sh = shell();
+    sh.context_mut().cwd = build.path().to_path_buf();
+
+    let res = sh.eval_program("plugin verify waver.wasm").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(
+        res.stdout.contains("ok    metadata      waver 1.0.0\n"),
+        "{}",
+        res.stdout
+    );
+    assert!(res.stdout.contains("warn  memory"), "{}", res.stdout);
+
+    // The greeter's manifest lists a command it does not export
+    let res = sh.eval_program("plugin verify greeter.wasm").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(
+        res.stdout
+            .contains("FAIL  exports       Plugin 'greeter' exports no function 'greet'"),
+        "{}",
+        res.stdout
+    );
+    assert_eq!(res.stderr, "plugin: greeter.wasm does not conform\n");
+
+    let res = sh.eval_program("plugin verify missing.wasm").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(
+        res.stderr.starts_with("plugin: cannot read "),
+        "{}",
+        res.stderr
+    );
+}
+
+#[test]
+fn shows_and_resets_recorded_permissions() {
+    let grants = PermissionGrants::new(config_dir().join(GRANTS_FILE));
+    grants
+        .record(
+            "greeter",
+            "1.0.0",
+            &[
+                ("file_read".to_string(), Decision::Granted),
+                ("network_request".to_string(), Decision::Denied),
+            ],
+        )
+        .unwrap();
+    grants
+        .record(
+            "counter",
+            "0.2.0",
+            &[("env_read".to_string(), Decision::Granted)],
+        )
+        .unwrap();
+    let mut sh = shell();
+
+    let res = sh.eval_program("plugin permissions").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "counter@0.2.0  env_read         granted\n\
+         greeter@1.0.0  file_read        granted\n\
+         greeter@1.0.0  network_request  denied\n"
+    );
+    let res = sh.eval_program("plugin permissions counter").unwrap();
+    assert_eq!(res.stdout, "counter@0.2.0  env_read  granted\n");
+
+    let res = sh
+        .eval_program("plugin permissions --reset greeter")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "forgot the permissions of greeter\n");
+    assert_eq!(grants.list().unwrap().len(), 1);
+    let res = sh.eval_program("plugin permissions -r greeter").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "plugin: no permissions are recorded for greeter\n"
+    );
+    let res = sh.eval_program("plugin permissions --reset").unwrap();
+    assert_eq!(res.exit_code, 2);
+}
+
+#[test]
+fn develops_a_plugin_reloading_it_when_rebuilt() {
+    let build = tempfile::tempdir().unwrap();
+    let artifact = build.path().join("waver.wasm");
+    write_waver(&artifact, "0.1.0", &["wave"]);
+    let artifact = artifact.canonicalize().unwrap();
+    let mut sh = shell();
+    sh.context_mut().cwd = build.path().to_path_buf();
+    let registry = sh.context_mut().plugin_commands.clone();
+
+    let res = sh.eval_program("plugin dev waver.wasm").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        format!("developing waver@0.1.0 from {}\n", artifact.display())
+    );
+    assert_eq!(registry.names(), ["wave"]);
+    let res = sh.eval_program("plugin dev waver.wasm").unwrap();
+    assert_eq!(res.exit_code, 1);
+
+    // A rebuild replaces the commands of the old version
+    write_waver(&artifact, "0.2.0", &["salute", "wave"]);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while registry.names() != ["salute", "wave"] {
+        assert!(Instant::now() < deadline, "the plugin was not reloaded");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(registry.get("wave").unwrap().plugin, "waver@0.2.0");
+    let res = sh.eval_program("plugin dev").unwrap();
+    assert_eq!(res.stdout, format!("waver@0.2.0  {}\n", artifact.display()));
+
+    let res = sh.eval_program("plugin dev --stop waver.wasm").unwrap();
+    assert_eq!(res.stdout, "stopped developing waver@0.2.0\n");
+    assert!(registry.names().is_empty());
+    let res = sh.eval_program("plugin dev --stop waver.wasm").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
@@ -0,0 +1,84 @@
+mod common;
+use nxsh_core::context::PositionalParams;
+use nxsh_core::{Shell, ShellState};
+
+fn shell_with_args(args: &[&str]) -> Shell {
+    let mut state = ShellState::new(Default::default()).unwrap();
+    state.positional =
+        PositionalParams::new("script.sh", args.iter().map(|a| a.to_string()).collect());
+    common::prepare(Shell::from_state(state))
+}
+
+#[test]
+fn expands_special_parameters() {
+    let mut sh = shell_with_args(&["a", "b", "c"]);
+    let res = sh.eval_program("echo $0 $# $1 $3 $@").unwrap();
+    assert_eq!(res.stdout, "script.sh 3 a c a b c\n");
+}
+
+#[test]
+fn exit_status_and_pid() {
+    let mut sh = shell_with_args(&[]);
+    let res = sh.eval_program("false\necho $?\ntrue\necho $?").unwrap();
+    assert_eq!(res.stdout, "1\n0\n");
+    let res = sh.eval_program("echo $$").unwrap();
+    assert_eq!(res.stdout, format!("{}\n", std::process::id()));
+}
+
+#[test]
+fn shift_drops_leading_parameters() {
+    let mut sh = shell_with_args(&["a", "b", "c"]);
+    let res = sh
+        .eval_program("shift\necho $# $1\nshift 2\necho $#")
+        .unwrap();
+    assert_eq!(res.stdout, "2 b\n0\n");
+    assert_eq!(sh.eval_program("shift").unwrap().exit_code, 1);
+}
+
+#[test]
+fn set_replaces_parameters() {
+    let mut sh = shell_with_args(&["a"]);
+    let res = sh.eval_program("set -- x y\necho $# $2").unwrap();
+    assert_eq!(res.stdout, "2 y\n");
+}
+
+#[test]
+fn getopts_walks_clustered_options() {
+    let mut sh = shell_with_args(&["-ab", "val", "-c", "rest"]);
+    let mut seen = Vec::new();
+    loop {
+        let res = sh.eval_program("getopts ab:c opt").unwrap();
+        if res.exit_code != 0 {
+            break;
+        }
+        let opt = sh.context().get_var("opt").unwrap();
+        let arg = sh.context().get_var("OPTARG").unwrap_or_default();
+        seen.push(format!("{opt}={arg}"));
+    }
+    assert_eq!(seen, vec!["a=", "b=val", "c="]);
+    assert_eq!(sh.context().get_var("OPTIND").as_deref(), Some("4"));
+}
+
+#[test]
+fn getopts_reports_errors() {
+    let mut sh = shell_with_args(&[]);
+    let res = sh.eval_program("getopts a opt -z").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(res.stderr.contains("illegal option -- z"));
+    assert_eq!(sh.context().get_var("opt").as_deref(), Some("?"));
+
+    sh.eval_program("OPTIND=1").unwrap();
+    sh.eval_program("getopts :a: opt -a").unwrap();
+    assert_eq!(sh.context().get_var("opt").as_deref(), Some(":"));
+    assert_eq!(sh.context().get_var("OPTARG").as_deref(), Some("a"));
+}
+
+#[test]
+fn dash_lists_active_option_flags() {
+    let mut sh = shell_with_args(&[]);
+    sh.context_mut().interactive = false;
+    let res = sh
+        .eval_program("set +mH\nset -eu\necho $-\nset +e -f\necho $-")
+        .unwrap();
+    assert_eq!(res.stdout, "ehu\nfhu\n");
+}
@@ -0,0 +1,36 @@
+mod common;
+use common::shell;
+
+#[test]
+fn formats_tables_from_repeated_arguments() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program(r#"printf '%-6s|%5.1f|%04x\n' alpha 3.14159 255 beta 2 16"#)
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "alpha |  3.1|00ff\nbeta  |  2.0|0010\n");
+
+    let res = sh.eval_program(r#"printf '%s:%q\n' 'a b' 'a b'"#).unwrap();
+    assert_eq!(res.stdout, "a b:a\\ b\n");
+}
+
+#[test]
+fn assigns_with_v_and_reports_errors() {
+    let mut sh = shell();
+    let res = sh.eval_program(r#"printf -v line '%03d-%s' 7 x"#).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "");
+    assert_eq!(sh.context().get_var("line").as_deref(), Some("007-x"));
+
+    let res = sh.eval_program("printf '%d\\n' 1x").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stdout, "1\n");
+    assert_eq!(res.stderr, "printf: 1x: invalid number\n");
+
+    let res = sh.eval_program("printf").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "printf: usage: printf [-v var] format [arguments]\n"
+    );
+}
@@ -0,0 +1,37 @@
+#![cfg(unix)]
+
+mod common;
+use common::shell;
+use std::process::Command;
+
+#[test]
+fn lists_a_selected_process() {
+    let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+    let pid = child.id();
+
+    let mut sh = shell();
+    sh.context_mut().interactive = false;
+
+    let res = sh
+        .eval_program(&format!("ps -o pid,comm -p {pid}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let width = pid.to_string().len().max(3);
+    assert_eq!(
+        res.stdout,
+        format!("{:>width$} COMMAND\n{pid} sleep\n", "PID")
+    );
+
+    let res = sh.eval_program(&format!("top -b -n 1 -p {pid}")).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.lines().any(|line| line.ends_with(" sleep")));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let res = sh
+        .eval_program(&format!("ps --no-headers -p {pid}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stdout, "");
+}
@@ -0,0 +1,131 @@
+mod common;
+use common::shell_with_input;
+use nxsh_core::Shell;
+use std::io::{self, Read};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+fn var(sh: &Shell, name: &str) -> Option<String> {
+    sh.context().get_var(name)
+}
+
+#[test]
+fn reads_line_into_reply() {
+    let mut sh = shell_with_input("  hello world  \nnext\n");
+    assert_eq!(sh.eval_program("read").unwrap().exit_code, 0);
+    assert_eq!(var(&sh, "REPLY").as_deref(), Some("  hello world  "));
+    sh.eval_program("read").unwrap();
+    assert_eq!(var(&sh, "REPLY").as_deref(), Some("next"));
+}
+
+#[test]
+fn splits_fields_with_remainder_in_last_name() {
+    let mut sh = shell_with_input("one two three four\n");
+    sh.eval_program("read a b rest").unwrap();
+    assert_eq!(var(&sh, "a").as_deref(), Some("one"));
+    assert_eq!(var(&sh, "b").as_deref(), Some("two"));
+    assert_eq!(var(&sh, "rest").as_deref(), Some("three four"));
+}
+
+#[test]
+fn honours_custom_ifs() {
+    let mut sh = shell_with_input("root:x:0\n");
+    sh.eval_program("IFS=:").unwrap();
+    sh.eval_program("read user pw uid").unwrap();
+    assert_eq!(var(&sh, "user").as_deref(), Some("root"));
+    assert_eq!(var(&sh, "uid").as_deref(), Some("0"));
+}
+
+#[test]
+fn backslashes_depend_on_raw_mode() {
+    let mut sh = shell_with_input("a\\ b\\\ncont\nc\\d\n");
+    sh.eval_program("read x").unwrap();
+    assert_eq!(var(&sh, "x").as_deref(), Some("a bcont"));
+    sh.eval_program("read -r y").unwrap();
+    assert_eq!(var(&sh, "y").as_deref(), Some("c\\d"));
+}
+
+#[test]
+fn quoted_separators_do_not_split() {
+    let mut sh = shell_with_input("a\\ b c\n");
+    sh.eval_program("read x y").unwrap();
+    assert_eq!(var(&sh, "x").as_deref(), Some("a b"));
+    assert_eq!(var(&sh, "y").as_deref(), Some("c"));
+}
+
+/// A stream with nothing to read until the test sends it, like a pipe
+/// whose writer is still running
+struct Quiet(mpsc::Receiver<u8>);
+
+impl Read for Quiet {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.recv() {
+            Ok(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn timeout_applies_to_quiet_streams() {
+    let (send, quiet) = mpsc::channel();
+    let mut sh = shell_with_input("");
+    sh.context_mut().stdin = Box::new(Quiet(quiet));
+    let started = Instant::now();
+    assert_eq!(sh.eval_program("read -t 0.2 x").unwrap().exit_code, 142);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // Input arriving later is still there for the next read
+    for byte in b"late\n" {
+        send.send(*byte).unwrap();
+    }
+    assert_eq!(sh.eval_program("read y").unwrap().exit_code, 0);
+    assert_eq!(var(&sh, "y").as_deref(), Some("late"));
+}
+
+#[test]
+fn huge_timeout_is_clamped() {
+    let mut sh = shell_with_input("ok\n");
+    assert_eq!(
+        sh.eval_program("read -t 100000000000000000000 x")
+            .unwrap()
+            .exit_code,
+        0
+    );
+    assert_eq!(var(&sh, "x").as_deref(), Some("ok"));
+}
+
+#[test]
+fn char_count_and_delimiter() {
+    let mut sh = shell_with_input("abcdef;rest");
+    sh.eval_program("read -n 3 x").unwrap();
+    assert_eq!(var(&sh, "x").as_deref(), Some("abc"));
+    sh.eval_program("read -d ';' y").unwrap();
+    assert_eq!(var(&sh, "y").as_deref(), Some("def"));
+}
+
+#[test]
+fn eof_sets_partial_value_and_fails() {
+    let mut sh = shell_with_input("partial");
+    assert_eq!(sh.eval_program("read x").unwrap().exit_code, 1);
+    assert_eq!(var(&sh, "x").as_deref(), Some("partial"));
+}
+
+#[test]
+fn array_assignment() {
+    let mut sh = shell_with_input("a b c\n");
+    sh.eval_program("read -a parts").unwrap();
+    assert_eq!(var(&sh, "parts[0]").as_deref(), Some("a"));
+    assert_eq!(var(&sh, "parts[2]").as_deref(), Some("c"));
+    assert_eq!(sh.context().get_array("parts").map(|a| a.len()), Some(3));
+}
+
+#[test]
+fn rejects_bad_usage() {
+    let mut sh = shell_with_input("");
+    assert_eq!(sh.eval_program("read -z").unwrap().exit_code, 2);
+    assert_eq!(sh.eval_program("read -n").unwrap().exit_code, 2);
+}
@@ -0,0 +1,71 @@
+mod common;
+use nxsh_core::Shell;
+use std::fs;
+use std::path::Path;
+
+/// A script's shell in `cwd`, answering questions with `input`
+fn shell(cwd: &Path, input: &str) -> Shell {
+    let mut sh = common::shell_with_input(input);
+    let ctx = sh.context_mut();
+    ctx.cwd = cwd.to_path_buf();
+    ctx.stderr = Box::new(std::io::sink());
+    sh
+}
+
+#[test]
+fn rm_reads_answers_or_assumes_them() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(dir.path().join(name), b"x").unwrap();
+    }
+    let mut sh = shell(dir.path(), "y\nn\n");
+
+    let res = sh.eval_program("rm -i a b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(!dir.path().join("a").exists());
+    assert!(dir.path().join("b").exists());
+
+    // The input is used up, and no is what the end of it answers
+    assert_eq!(sh.eval_program("rm -i b").unwrap().exit_code, 0);
+    assert!(dir.path().join("b").exists());
+
+    sh.eval_program("NXSH_ASSUME=yes").unwrap();
+    let res = sh.eval_program("rm -iv b c").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "removed 'b'\nremoved 'c'\n");
+}
+
+#[test]
+fn cp_and_mv_keep_or_back_up_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let content = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+    fs::write(dir.path().join("a"), "one").unwrap();
+    fs::write(dir.path().join("b"), "two").unwrap();
+    let mut sh = shell(dir.path(), "");
+
+    let res = sh.eval_program("cp -n a b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(content("b"), "two");
+
+    let res = sh.eval_program("cp -bv a b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "'a' -> 'b'\n");
+    assert_eq!((content("b"), content("b~")), ("one".into(), "two".into()));
+
+    // No newer than b, so -u leaves it
+    fs::write(dir.path().join("b"), "three").unwrap();
+    assert_eq!(sh.eval_program("mv -u a b").unwrap().exit_code, 0);
+    assert_eq!(content("a"), "one");
+
+    sh.eval_program("NXSH_ASSUME=no").unwrap();
+    assert_eq!(sh.eval_program("mv -i a b").unwrap().exit_code, 0);
+    assert_eq!(content("b"), "three");
+
+    let res = sh.eval_program("mv -b -S .old a b").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(!dir.path().join("a").exists());
+    assert_eq!(
+        (content("b"), content("b.old")),
+        ("one".into(), "three".into())
+    );
+}
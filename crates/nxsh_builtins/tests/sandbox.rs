@@ -0,0 +1,83 @@
+mod common;
+use nxsh_core::Shell;
+use std::path::PathBuf;
+use std::sync::Once;
+
+/// Profiles are read from one directory for the whole test process
+fn config_dir() -> PathBuf {
+    static SET: Once = Once::new();
+    let dir = std::env::temp_dir().join(format!("nxsh-sandbox-test-{}", std::process::id()));
+    SET.call_once(|| {
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("sandbox.toml"),
+            "[profile.quick]\ntimeout = \"0.2s\"\n\n[profile.offline]\nprocesses = 4096\n",
+        )
+        .unwrap();
+        std::env::set_var("NXSH_CONFIG_DIR", &dir);
+    });
+    dir
+}
+
+fn shell() -> Shell {
+    config_dir();
+    common::shell()
+}
+
+#[test]
+fn lists_and_shows_profiles() {
+    let mut sh = shell();
+    let res = sh.eval_program("sandbox profiles").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(
+        res.stdout,
+        "offline   4096 processes\n\
+         quick     timeout 0.2s\n\
+         readonly  read-only except /dev/null\n\
+         strict    network off, read-only except /dev/null, cpu 1m, memory 1G, timeout 5m, 256 files\n"
+    );
+
+    let res = sh.eval_program("sandbox show strict").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(
+        res.stdout,
+        "[profile.strict]\nnetwork = false\nwritable = [\"/dev/null\"]\ncpu = \"1m\"\n\
+         memory = \"1G\"\ntimeout = \"5m\"\nfiles = 256\n"
+    );
+}
+
+#[test]
+fn reports_usage_and_lookup_errors() {
+    let mut sh = shell();
+    let res = sh.eval_program("sandbox").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res
+        .stderr
+        .starts_with("sandbox: a subcommand is required\nsandbox: usage: sandbox run "));
+
+    let res = sh.eval_program("sandbox run -p nothing -- ls").unwrap();
+    assert_eq!(res.exit_code, 125);
+    assert_eq!(res.stderr, "sandbox: nothing: no such profile\n");
+
+    let res = sh.eval_program("sandbox run nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert_eq!(
+        res.stderr,
+        "sandbox: nxsh-no-such-command: command not found\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_commands_under_limits() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("sandbox run -f 64 -- echo contained")
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "contained\n");
+
+    let res = sh.eval_program("sandbox run -p quick -- sleep 5").unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert_eq!(res.stderr, "sandbox: sleep: timed out after 0.2s\n");
+}
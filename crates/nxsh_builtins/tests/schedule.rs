@@ -0,0 +1,118 @@
+#![cfg(feature = "scheduler")]
+
+mod common;
+use nxsh_core::Shell;
+use std::path::PathBuf;
+use std::sync::Once;
+
+/// The job table lives in one directory for the whole test process
+fn config_dir() -> PathBuf {
+    static SET: Once = Once::new();
+    let dir = std::env::temp_dir().join(format!("nxsh-schedule-test-{}", std::process::id()));
+    SET.call_once(|| std::env::set_var("NXSH_CONFIG_DIR", &dir));
+    dir
+}
+
+fn shell(root: &std::path::Path) -> Shell {
+    config_dir();
+    common::shell_in(root)
+}
+
+/// The job ID from a `job ID ...` line
+fn job_id(stdout: &str) -> String {
+    stdout
+        .split_whitespace()
+        .nth(1)
+        .unwrap()
+        .trim_end_matches(',')
+        .to_string()
+}
+
+#[test]
+fn reports_usage_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell(dir.path());
+
+    let res = sh.eval_program("at").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "at: missing time\n");
+
+    let res = sh.eval_program("at 25:00 true").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "at: garbled time '25:00'\n");
+
+    let res = sh.eval_program("cron '61 * * * *' true").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "cron: invalid expression '61 * * * *': minute '61' is not in 0-59\n"
+    );
+
+    let res = sh.eval_program("schedule add --in soon true").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "schedule: invalid duration 'soon'\n");
+
+    let res = sh.eval_program("schedule frobnicate").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "schedule: unknown subcommand 'frobnicate'\n");
+}
+
+#[test]
+fn keeps_jobs_in_the_config_dir() {
+    let config = config_dir();
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell(dir.path());
+
+    let res = sh
+        .eval_program("at 23:59 tomorrow echo 'good night'")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.starts_with("job at_"), "{}", res.stdout);
+    assert!(res.stdout.ends_with(" 23:59\n"), "{}", res.stdout);
+    let once = job_id(&res.stdout);
+
+    let res = sh.eval_program("cron '0 4 * * *' backup.sh").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.ends_with(" 04:00\n"), "{}", res.stdout);
+    let nightly = job_id(&res.stdout);
+
+    let res = sh.eval_program("schedule add --every 15m 'df -h'").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let every = job_id(&res.stdout);
+
+    let res = sh.eval_program("schedule").unwrap();
+    assert!(res.stdout.starts_with("ID "), "{}", res.stdout);
+    for id in [&once, &nightly, &every] {
+        assert!(res.stdout.contains(id.as_str()), "{}", res.stdout);
+    }
+    assert!(res.stdout.contains("every 15m"), "{}", res.stdout);
+
+    let res = sh.eval_program("at -l").unwrap();
+    assert!(res.stdout.contains(&once) && res.stdout.contains("echo 'good night'"));
+    assert!(!res.stdout.contains(&nightly) && !res.stdout.contains(&every));
+
+    // The table on disk is what another shell starts from
+    let table = std::fs::read_to_string(config.join("schedule.json")).unwrap();
+    assert!(table.contains("backup.sh"), "{table}");
+    assert!(table.contains(&dir.path().display().to_string()), "{table}");
+
+    let res = sh
+        .eval_program(&format!("schedule disable {every}"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh.eval_program("schedule list").unwrap();
+    assert!(res.stdout.contains("disabled"), "{}", res.stdout);
+
+    let res = sh
+        .eval_program(&format!(
+            "cron -r {nightly} nope_1; schedule rm {once} {every}"
+        ))
+        .unwrap();
+    assert_eq!(res.stderr, "cron: nope_1: no such job\n");
+    let res = sh.eval_program("schedule").unwrap();
+    for id in [&once, &nightly, &every] {
+        assert!(!res.stdout.contains(id.as_str()), "{}", res.stdout);
+    }
+
+    let _ = std::fs::remove_dir_all(config);
+}
@@ -0,0 +1,52 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn edits_standard_input() {
+    let mut sh = shell_with_input("key = value\n# comment\nname = nxsh\n");
+    let res = sh
+        .eval_program(r"sed -n -E '/^#/!s/^(\w+) = (.*)$/\2=\1/p'")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "value=key\nnxsh=name\n");
+}
+
+#[test]
+fn edits_files_in_place_with_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    std::fs::write(&a, "one\ntwo\n").unwrap();
+    std::fs::write(&b, "three\n").unwrap();
+
+    let mut sh = shell_with_input("");
+    let res = sh
+        .eval_program(&format!(
+            "sed -i.bak -e '1i\\' -e 'first' -e 's/o/0/g' {} {}",
+            a.display(),
+            b.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "");
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "first\n0ne\ntw0\n");
+    assert_eq!(std::fs::read_to_string(&b).unwrap(), "first\nthree\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.txt.bak")).unwrap(),
+        "one\ntwo\n"
+    );
+
+    let res = sh
+        .eval_program(&format!("sed p {}", dir.path().join("missing").display()))
+        .unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(
+        res.stderr.contains("No such file or directory"),
+        "{}",
+        res.stderr
+    );
+
+    let res = sh.eval_program("sed 's/a/b'").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.starts_with("sed: "), "{}", res.stderr);
+}
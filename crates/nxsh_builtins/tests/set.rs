@@ -0,0 +1,94 @@
+mod common;
+use common::shell;
+use nxsh_core::Executor;
+
+#[test]
+fn failures_continue_without_errexit() {
+    let mut sh = shell();
+    let res = sh.eval_program("false\necho after").unwrap();
+    assert_eq!(res.stdout, "after\n");
+    assert_eq!(res.exit_code, 0);
+}
+
+#[test]
+fn errexit_stops_at_first_failure() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("set -e\necho before\nfalse\necho after")
+        .unwrap();
+    assert_eq!(res.stdout, "before\n");
+    assert_eq!(res.exit_code, 1);
+}
+
+#[test]
+fn errexit_ignores_tested_commands() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("set -e\nif false; then echo no; fi\nfalse || echo handled\necho done")
+        .unwrap();
+    assert_eq!(res.stdout, "handled\ndone\n");
+}
+
+#[test]
+fn nounset_rejects_unset_variables() {
+    let mut sh = shell();
+    sh.eval_program("set -u").unwrap();
+    let err = sh.eval_program("echo $NXSH_SURELY_UNSET").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("NXSH_SURELY_UNSET: unbound variable"));
+    sh.eval_program("set +u").unwrap();
+    assert!(sh.eval_program("echo $NXSH_SURELY_UNSET").is_ok());
+}
+
+#[test]
+fn pipefail_reports_rightmost_failure() {
+    let mut sh = shell();
+    assert_eq!(sh.eval_program("false | true").unwrap().exit_code, 0);
+    sh.eval_program("set -o pipefail").unwrap();
+    assert_eq!(sh.eval_program("false | true").unwrap().exit_code, 1);
+    assert_eq!(sh.eval_program("true | true").unwrap().exit_code, 0);
+}
+
+#[test]
+fn xtrace_line_quotes_words() {
+    let sh = shell();
+    let trace = Executor::format_trace(
+        "echo",
+        &["a b".to_string(), "plain".to_string()],
+        sh.context(),
+    );
+    assert_eq!(trace, "+ echo 'a b' plain\n");
+}
+
+#[test]
+fn set_rejects_unknown_options() {
+    let mut sh = shell();
+    let res = sh.eval_program("set -o nosuchthing").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res.stderr.contains("invalid option name"));
+    assert_eq!(sh.eval_program("set -Q").unwrap().exit_code, 2);
+}
+
+#[test]
+fn set_o_lists_current_state() {
+    let mut sh = shell();
+    sh.eval_program("set -ex").unwrap();
+    let res = sh.eval_program("set +o").unwrap();
+    assert!(res.stdout.contains("set -o errexit\n"));
+    assert!(res.stdout.contains("set -o xtrace\n"));
+    assert!(res.stdout.contains("set +o nounset\n"));
+}
+
+#[test]
+fn shopt_toggles_and_queries() {
+    let mut sh = shell();
+    assert_eq!(sh.eval_program("shopt -q extglob").unwrap().exit_code, 1);
+    sh.eval_program("shopt -s extglob nullglob").unwrap();
+    assert_eq!(sh.eval_program("shopt -q extglob").unwrap().exit_code, 0);
+    assert!(sh.context().get_option("nullglob").unwrap());
+    let res = sh.eval_program("shopt -p extglob").unwrap();
+    assert_eq!(res.stdout, "shopt -s extglob\n");
+    let res = sh.eval_program("shopt -s bogus").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
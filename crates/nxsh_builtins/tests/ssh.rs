@@ -0,0 +1,62 @@
+#![cfg(feature = "remote")]
+
+mod common;
+use common::shell_in;
+use std::net::TcpListener;
+
+/// A local port nothing listens on
+fn closed_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[test]
+fn reports_usage_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+
+    let res = sh.eval_program("ssh").unwrap();
+    assert_eq!(res.exit_code, 255);
+    assert!(res.stderr.starts_with("usage: ssh "), "{}", res.stderr);
+
+    let res = sh.eval_program("ssh -x host").unwrap();
+    assert_eq!(res.exit_code, 255);
+    assert!(res.stderr.starts_with("unknown option -- x\nusage: ssh "));
+
+    let res = sh.eval_program("scp only-one").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.starts_with("usage: scp "), "{}", res.stderr);
+
+    let res = sh.eval_program("scp a b").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "scp: neither 'a' nor 'b' is on another host; use cp\n"
+    );
+}
+
+#[test]
+fn reports_refused_connections() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    let port = closed_port();
+
+    let res = sh
+        .eval_program(&format!("ssh -o BatchMode=yes -p {port} 127.0.0.1 true"))
+        .unwrap();
+    assert_eq!(res.exit_code, 255);
+    assert_eq!(
+        res.stderr,
+        format!("ssh: connect to host 127.0.0.1 port {port}: Connection refused\n")
+    );
+
+    std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+    let res = sh
+        .eval_program(&format!("scp -o BatchMode=yes -P {port} a.txt 127.0.0.1:"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        format!("scp: connect to host 127.0.0.1 port {port}: Connection refused\n")
+    );
+}
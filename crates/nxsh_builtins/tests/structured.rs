@@ -0,0 +1,194 @@
+mod common;
+use common::shell;
+
+#[test]
+fn ls_rows_flow_through_filters() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("small.txt"), "hello").unwrap();
+    std::fs::write(dir.path().join("big.txt"), vec![b'x'; 300]).unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    let path = dir.path().display();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "ls {path} | where type -eq file | sort-by -r size | select name size"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "big.txt\t300\nsmall.txt\t5\n");
+
+    let res = sh
+        .eval_program(&format!("ls {path} | where size -lt 100 | length"))
+        .unwrap();
+    // The directory's own size depends on the file system
+    assert!(res.stdout == "1\n" || res.stdout == "2\n", "{}", res.stdout);
+
+    let res = sh
+        .eval_program(&format!("ls {path} | first 2 | select name"))
+        .unwrap();
+    assert_eq!(res.stdout, "big.txt\nsmall.txt\n");
+}
+
+#[test]
+fn filters_need_a_structured_producer() {
+    let mut sh = shell();
+    let res = sh.eval_program("where size -gt 1 | length").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "nxsh: where: expects input from a structured command\n"
+    );
+
+    let res = sh.eval_program("ls | where size -between 1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("-between: unknown operator"));
+}
+
+#[test]
+fn json_and_csv_convert_through_filters() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("data.json");
+    std::fs::write(
+        &json,
+        r#"[{"name": "a", "size": 50}, {"name": "b, c", "size": 150}, {"name": "d", "size": 300}]"#,
+    )
+    .unwrap();
+    let csv = dir.path().join("data.csv");
+    std::fs::write(&csv, "name,size\nx,1\n\"y \"\"z\"\"\",2\n").unwrap();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "cat {} | from-json | where size -gt 100 | to-csv",
+            json.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "name,size\n\"b, c\",150\nd,300\n");
+
+    let res = sh
+        .eval_program(&format!("from-csv {} | to-json --compact", csv.display()))
+        .unwrap();
+    assert_eq!(
+        res.stdout,
+        "[{\"name\":\"x\",\"size\":1},{\"name\":\"y \\\"z\\\"\",\"size\":2}]\n"
+    );
+
+    let res = sh.eval_program("echo '{' | from-json").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.starts_with("nxsh: from-json: invalid JSON"));
+}
+
+#[test]
+fn queries_combine_conditions_keys_and_groups() {
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("files.csv");
+    std::fs::write(
+        &csv,
+        "name,type,size\na.rs,file,120\nb.txt,file,30\nsrc,dir,4096\nc.rs,file,900\n",
+    )
+    .unwrap();
+    let csv = csv.display();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "from-csv {csv} | where \"type == file && (size -gt 100 || name =~ .txt)\" | sort-by size:desc | select name"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "c.rs\na.rs\nb.txt\n");
+
+    let res = sh
+        .eval_program(&format!(
+            "from-csv {csv} | group-by type --sum size --max name | sort-by type"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "dir\t1\t4096\tsrc\nfile\t3\t1050\tc.rs\n");
+
+    let res = sh
+        .eval_program(&format!("from-csv {csv} | where not size"))
+        .unwrap();
+    assert_eq!(res.stdout, "");
+}
+
+#[test]
+fn yaml_and_toml_round_trip_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let toml = dir.path().join("config.toml");
+    let config =
+        "# Build settings\n\nname = \"nxsh\"\njobs = 4\n\n[profile]\nopt = 3\ndebug = false\n";
+    std::fs::write(&toml, config).unwrap();
+    let toml = toml.display();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("from-toml {toml} | to-toml"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "# Build settings\nname = \"nxsh\"\njobs = 4\n\n[profile]\nopt = 3\ndebug = false\n"
+    );
+
+    let res = sh
+        .eval_program(&format!(
+            "from-toml {toml} | to-yaml | from-yaml | to-json -c"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "{\"name\":\"nxsh\",\"jobs\":4,\"profile\":{\"opt\":3,\"debug\":false}}\n"
+    );
+
+    let res = sh.eval_program("echo '- 1' | from-yaml | to-toml").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "nxsh: to-toml: expects a record, got list\n");
+}
+
+#[test]
+fn paths_get_update_and_flatten_nested_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("user.json");
+    std::fs::write(
+        &json,
+        r#"{"user": {"name": "ann", "addresses": [{"city": "Oslo", "zip": "0150"}, {"city": "Rome", "zip": "00100"}]}}"#,
+    )
+    .unwrap();
+    let json = json.display();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "cat {json} | from-json | get user.addresses.1.city"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "Rome\n");
+
+    let res = sh
+        .eval_program(&format!(
+            "from-json {json} | update user.addresses.zip none | update user.age 41 | get user | to-json -c"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "{\"name\":\"ann\",\"addresses\":[{\"city\":\"Oslo\",\"zip\":\"none\"},{\"city\":\"Rome\",\"zip\":\"none\"}],\"age\":41}\n"
+    );
+
+    let res = sh
+        .eval_program(&format!("from-json {json} | get user | flatten"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "ann\tOslo\t0150\nann\tRome\t00100\n");
+
+    let res = sh
+        .eval_program(&format!("from-json {json} | get user.email"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "nxsh: get: email: no such field\n");
+}
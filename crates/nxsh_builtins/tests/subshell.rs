@@ -0,0 +1,73 @@
+mod common;
+use common::shell;
+
+#[test]
+fn variables_stay_inside() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("x=1\n(x=2; a=(y z); echo $x)\necho $x ${#a[@]}")
+        .unwrap();
+    assert_eq!(res.stdout, "2\n1 0\n");
+}
+
+#[test]
+fn directory_change_does_not_leak() {
+    let mut sh = shell();
+    let before = sh.context().cwd.clone();
+    let process_before = std::env::current_dir().unwrap();
+    let tmp = std::env::temp_dir();
+    let res = sh
+        .eval_program(&format!("(cd {} && echo moved)", tmp.display()))
+        .unwrap();
+    assert_eq!(res.stdout, "moved\n");
+    assert_eq!(sh.context().cwd, before);
+    assert_eq!(std::env::current_dir().unwrap(), process_before);
+}
+
+#[test]
+fn concurrent_subshells_keep_their_own_directory() {
+    let process_before = std::env::current_dir().unwrap();
+    let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+    let done = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = dirs
+            .iter()
+            .map(|dir| {
+                let target = dir.path().canonicalize().unwrap();
+                scope.spawn(move || {
+                    let mut sh = shell();
+                    for _ in 0..50 {
+                        let res = sh
+                            .eval_program(&format!("(cd {} && echo $PWD)", target.display()))
+                            .unwrap();
+                        assert_eq!(res.stdout, format!("{}\n", target.display()));
+                    }
+                })
+            })
+            .collect();
+        // Neither subshell may move the process while the other runs
+        scope.spawn(|| {
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                assert_eq!(std::env::current_dir().unwrap(), process_before);
+            }
+        });
+        let results: Vec<_> = workers.into_iter().map(|worker| worker.join()).collect();
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        for result in results {
+            result.unwrap();
+        }
+    });
+    assert_eq!(std::env::current_dir().unwrap(), process_before);
+}
+
+#[test]
+fn status_and_errexit_are_the_subshells() {
+    let mut sh = shell();
+    let res = sh.eval_program("(false) || echo caught").unwrap();
+    assert_eq!(res.stdout, "caught\n");
+    let res = sh
+        .eval_program("(set -e; false; echo unreachable); echo after")
+        .unwrap();
+    assert_eq!(res.stdout, "after\n");
+    assert_eq!(sh.context().get_option("errexit").ok(), Some(false));
+}
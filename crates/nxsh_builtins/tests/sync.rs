@@ -0,0 +1,223 @@
+mod common;
+use common::shell_in;
+use filetime::FileTime;
+use std::fs;
+use std::path::Path;
+
+/// Write `content` to `path`, creating its directory, with a set time
+fn write(path: &Path, content: &str, mtime: i64) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+    filetime::set_file_mtime(path, FileTime::from_unix_time(mtime, 0)).unwrap();
+}
+
+fn mtime(path: &Path) -> i64 {
+    FileTime::from_last_modification_time(&fs::metadata(path).unwrap()).unix_seconds()
+}
+
+#[test]
+fn copies_only_what_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(&root.join("src/a.txt"), "a", 1_000_000);
+    write(&root.join("src/sub/b.txt"), "bb", 1_000_000);
+    let mut sh = shell_in(root);
+
+    let res = sh.eval_program("sync -v src backup").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "created directory backup/src\n\
+         src/a.txt\n\
+         src/sub/\n\
+         src/sub/b.txt\n\
+         2 files transferred (3 bytes), 0 deleted\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("backup/src/sub/b.txt")).unwrap(),
+        "bb"
+    );
+    assert_eq!(mtime(&root.join("backup/src/a.txt")), 1_000_000);
+
+    let res = sh.eval_program("sync -v src backup").unwrap();
+    assert_eq!(res.stdout, "0 files transferred (0 bytes), 0 deleted\n");
+
+    write(&root.join("src/a.txt"), "A", 2_000_000);
+    let res = sh.eval_program("sync -av src backup").unwrap();
+    assert_eq!(
+        res.stdout,
+        "src/a.txt\n1 file transferred (1 bytes), 0 deleted\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("backup/src/a.txt")).unwrap(),
+        "A"
+    );
+
+    // A trailing slash copies the contents, and rcp is the same command
+    let res = sh.eval_program("rcp src/ copy").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "");
+    assert!(root.join("copy/a.txt").exists());
+    assert!(root.join("copy/sub/b.txt").exists());
+}
+
+#[test]
+fn compares_content_with_checksum() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(&root.join("src/a.txt"), "new", 1_000_000);
+    write(&root.join("dest/a.txt"), "old", 1_000_000);
+    let mut sh = shell_in(root);
+
+    let res = sh.eval_program("sync src/ dest").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(fs::read_to_string(root.join("dest/a.txt")).unwrap(), "old");
+
+    let res = sh.eval_program("sync -cv src/ dest").unwrap();
+    assert_eq!(
+        res.stdout,
+        "a.txt\n1 file transferred (3 bytes), 0 deleted\n"
+    );
+    assert_eq!(fs::read_to_string(root.join("dest/a.txt")).unwrap(), "new");
+
+    // With -u a newer file in the destination stays
+    write(&root.join("dest/a.txt"), "newest", 3_000_000);
+    let res = sh.eval_program("sync -uv src/ dest").unwrap();
+    assert_eq!(res.stdout, "0 files transferred (0 bytes), 0 deleted\n");
+}
+
+#[test]
+fn deletes_extra_files_but_not_excluded_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(&root.join("src/a.txt"), "a", 1_000_000);
+    write(&root.join("src/debug.log"), "log", 1_000_000);
+    write(&root.join("dest/a.txt"), "a", 1_000_000);
+    write(&root.join("dest/extra.txt"), "x", 1_000_000);
+    write(&root.join("dest/keep.log"), "kept", 1_000_000);
+    write(&root.join("dest/old/x.txt"), "x", 1_000_000);
+    let mut sh = shell_in(root);
+
+    let res = sh
+        .eval_program("sync -n --delete --exclude '*.log' src/ dest")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        "deleting old/x.txt\n\
+         deleting old/\n\
+         deleting extra.txt\n\
+         0 files transferred (0 bytes), 3 deleted (DRY RUN)\n"
+    );
+    assert!(root.join("dest/extra.txt").exists());
+
+    let res = sh
+        .eval_program("sync --delete --exclude '*.log' src/ dest")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(!root.join("dest/extra.txt").exists());
+    assert!(!root.join("dest/old").exists());
+    assert!(!root.join("dest/debug.log").exists());
+    assert_eq!(
+        fs::read_to_string(root.join("dest/keep.log")).unwrap(),
+        "kept"
+    );
+}
+
+#[test]
+fn includes_override_later_excludes() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(&root.join("src/lib.rs"), "lib", 1_000_000);
+    write(&root.join("src/lib.o"), "obj", 1_000_000);
+    write(&root.join("src/bin/main.rs"), "main", 1_000_000);
+    write(&root.join("src/target/out.rs"), "out", 1_000_000);
+    let mut sh = shell_in(root);
+
+    let res = sh
+        .eval_program(
+            "sync --exclude /target --include '*/' --include '*.rs' --exclude '*' src/ out",
+        )
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(root.join("out/lib.rs").exists());
+    assert!(root.join("out/bin/main.rs").exists());
+    assert!(!root.join("out/lib.o").exists());
+    assert!(!root.join("out/target").exists());
+}
+
+#[test]
+fn reports_bad_operands() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+
+    let res = sh.eval_program("sync src").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "sync: missing destination file operand after 'src'\n"
+    );
+
+    let res = sh.eval_program("rcp").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "rcp: missing file operand\n");
+
+    let res = sh.eval_program("sync missing out").unwrap();
+    assert_eq!(res.exit_code, 23);
+    assert_eq!(
+        res.stderr,
+        "sync: cannot stat 'missing': No such file or directory\n"
+    );
+
+    // Without operands sync flushes the buffers
+    let res = sh.eval_program("sync").unwrap();
+    assert_eq!(res.exit_code, 0);
+}
+
+#[cfg(not(feature = "remote"))]
+#[test]
+fn needs_the_remote_feature_for_hosts() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    let res = sh.eval_program("sync src host:dest").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "sync: 'host:dest' is on another host, which needs a build with the 'remote' feature\n"
+    );
+}
+
+#[cfg(all(unix, feature = "remote"))]
+#[test]
+fn copies_to_and_from_hosts() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(&root.join("src/a.txt"), "a", 1_000_000);
+    write(&root.join("src/sub/b.txt"), "bb", 1_000_000);
+    // A stand-in for ssh that runs the command here
+    fs::write(root.join("fake-ssh"), "shift\nexec sh -c \"$1\"\n").unwrap();
+    let mut sh = shell_in(root);
+
+    let remote = root.join("remote");
+    let command = format!(
+        "sync -v -e 'sh {}' src/ host:{}",
+        root.join("fake-ssh").display(),
+        remote.display()
+    );
+    let res = sh.eval_program(&command).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(fs::read_to_string(remote.join("sub/b.txt")).unwrap(), "bb");
+    assert_eq!(mtime(&remote.join("a.txt")), 1_000_000);
+    let res = sh.eval_program(&command).unwrap();
+    assert_eq!(res.stdout, "0 files transferred (0 bytes), 0 deleted\n");
+
+    let res = sh
+        .eval_program(&format!(
+            "sync -e 'sh {}' host:{}/ back",
+            root.join("fake-ssh").display(),
+            remote.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(fs::read_to_string(root.join("back/a.txt")).unwrap(), "a");
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "compression-tar")]
+
+mod common;
+use common::shell;
+
+#[test]
+fn round_trips_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+    std::fs::create_dir_all(dir.path().join("src/sub")).unwrap();
+    std::fs::write(dir.path().join("src/sub/note.txt"), "kept\n").unwrap();
+    std::fs::write(dir.path().join("src/skip.log"), "dropped\n").unwrap();
+    std::fs::create_dir(dir.path().join("out")).unwrap();
+
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "tar -cf {root}/a.tar -C {root} --exclude='*.log' src"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+    let res = sh.eval_program(&format!("tar -tf {root}/a.tar")).unwrap();
+    assert_eq!(res.stdout, "src/\nsrc/sub/\nsrc/sub/note.txt\n");
+
+    let res = sh
+        .eval_program(&format!("tar -xvf {root}/a.tar -C {root}/out"))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "src/\nsrc/sub/\nsrc/sub/note.txt\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("out/src/sub/note.txt")).unwrap(),
+        "kept\n"
+    );
+}
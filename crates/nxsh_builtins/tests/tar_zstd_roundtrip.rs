@@ -1,7 +1,8 @@
+#![cfg(all(feature = "compression-tar", feature = "compression-zstd"))]
+
 use nxsh_builtins::tar::tar_cli;
 
 #[test]
-#[ignore] // TODO: Implement tar --zstd functionality
 fn tar_zstd_store_mode_roundtrip() {
     let dir = tempfile::tempdir().unwrap();
     let work = dir.path();
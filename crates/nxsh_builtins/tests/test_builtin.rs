@@ -0,0 +1,47 @@
+mod common;
+use common::shell_in;
+
+#[test]
+fn branches_on_files_in_the_working_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("foo"), "x").unwrap();
+    let mut sh = shell_in(dir.path());
+
+    let res = sh
+        .eval_program("if [ -f foo ]; then echo yes; else echo no; fi")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "yes\n");
+
+    let res = sh
+        .eval_program("if [ -d foo -o ! -e bar ]; then echo yes; fi")
+        .unwrap();
+    assert_eq!(res.stdout, "yes\n");
+
+    let res = sh.eval_program("test -s foo && test ! -e bar").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+}
+
+#[test]
+fn compares_variables_and_reports_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(dir.path());
+    sh.eval_program("n=12; name=nexus").unwrap();
+
+    let res = sh
+        .eval_program(r#"[ "$n" -gt 9 -a "$name" = nexus ]"#)
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh
+        .eval_program(r#"[ '(' "$n" -lt 9 -o -z "$name" ')' -o ! -n "$n" ]"#)
+        .unwrap();
+    assert_eq!(res.exit_code, 1, "{}", res.stderr);
+
+    let res = sh.eval_program(r#"[ "$name" -eq 1 ]"#).unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "[: nexus: integer expression expected\n");
+
+    let res = sh.eval_program("[ -n x").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(res.stderr, "[: missing `]'\n");
+}
@@ -0,0 +1,53 @@
+mod common;
+use common::shell_with_input;
+
+#[test]
+fn reshapes_standard_input() {
+    let mut sh = shell_with_input("one\ntwo\nthree\n");
+    let res = sh.eval_program("tac").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "three\ntwo\none\n");
+
+    let mut sh = shell_with_input("one\ntwo\nthree\n");
+    let res = sh.eval_program("paste -sd, -").unwrap();
+    assert_eq!(res.stdout, "one,two,three\n");
+
+    let mut sh = shell_with_input("one\ntwo\n");
+    let res = sh.eval_program("nl -w1 -s.").unwrap();
+    assert_eq!(res.stdout, "1.one\n2.two\n");
+
+    let mut sh = shell_with_input("abc\n");
+    let res = sh.eval_program("rev").unwrap();
+    assert_eq!(res.stdout, "cba\n");
+}
+
+#[test]
+fn writes_and_combines_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().display().to_string();
+
+    let mut sh = shell_with_input("1 a\n2 b\n3 c\n");
+    let res = sh.eval_program(&format!("tee {root}/ids")).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "1 a\n2 b\n3 c\n");
+
+    let res = sh
+        .eval_program(&format!("split -l 2 {root}/ids {root}/part."))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("part.ab")).unwrap(),
+        "3 c\n"
+    );
+
+    std::fs::write(dir.path().join("names"), "1 ann\n3 cy\n").unwrap();
+    let res = sh
+        .eval_program(&format!("join {root}/ids {root}/names"))
+        .unwrap();
+    assert_eq!(res.stdout, "1 a ann\n3 c cy\n");
+
+    let res = sh
+        .eval_program(&format!("comm -12 {root}/ids {root}/part.aa"))
+        .unwrap();
+    assert_eq!(res.stdout, "1 a\n2 b\n");
+}
@@ -0,0 +1,50 @@
+mod common;
+use common::shell;
+use nxsh_hal::signal::{self, ShellSignal};
+
+#[test]
+fn exit_trap_runs_once() {
+    let mut sh = shell();
+    sh.eval_program("trap 'echo bye' EXIT").unwrap();
+    let first = sh.run_exit_trap().unwrap();
+    assert_eq!(first.stdout, "bye\n");
+    let second = sh.run_exit_trap().unwrap();
+    assert!(second.stdout.is_empty());
+}
+
+#[test]
+fn debug_trap_precedes_each_command() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("trap 'echo dbg' DEBUG\necho one\necho two")
+        .unwrap();
+    assert_eq!(res.stdout, "dbg\none\ndbg\ntwo\n");
+}
+
+#[test]
+fn signal_trap_runs_between_commands() {
+    let mut sh = shell();
+    sh.eval_program("trap 'echo caught' USR1").unwrap();
+    signal::mark_pending(ShellSignal::User1);
+    let res = sh.eval_program("echo work").unwrap();
+    assert_eq!(res.stdout, "work\ncaught\n");
+    sh.eval_program("trap - USR1").unwrap();
+}
+
+#[test]
+fn print_lists_registered_traps() {
+    let mut sh = shell();
+    sh.eval_program("trap 'echo done' EXIT").unwrap();
+    sh.eval_program("trap '' QUIT").unwrap();
+    let res = sh.eval_program("trap -p").unwrap();
+    assert_eq!(res.stdout, "trap -- 'echo done' EXIT\ntrap -- '' SIGQUIT\n");
+    sh.eval_program("trap - QUIT").unwrap();
+}
+
+#[test]
+fn invalid_condition_reports_error() {
+    let mut sh = shell();
+    let res = sh.eval_program("trap 'echo x' NOPE").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("invalid signal specification"));
+}
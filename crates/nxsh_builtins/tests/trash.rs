@@ -0,0 +1,92 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+mod common;
+use common::shell_in;
+use std::fs;
+
+// `trash empty` is left out: it would empty the real volume trashes too
+#[test]
+fn trashes_lists_and_restores_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let home = root.join("data");
+    std::env::set_var("XDG_DATA_HOME", &home);
+    fs::create_dir(root.join("work")).unwrap();
+    fs::write(root.join("work/a.txt"), b"first").unwrap();
+    fs::create_dir(root.join("work/d")).unwrap();
+    fs::write(root.join("work/d/inner"), b"x").unwrap();
+    let mut sh = shell_in(&root.join("work"));
+
+    let res = sh.eval_program("trash -v a.txt").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "trashed 'a.txt'\n");
+    assert!(!root.join("work/a.txt").exists());
+    assert_eq!(fs::read(home.join("Trash/files/a.txt")).unwrap(), b"first");
+    let info = fs::read_to_string(home.join("Trash/info/a.txt.trashinfo")).unwrap();
+    assert!(info.contains(&format!("Path={}/work/a.txt\n", root.display())));
+
+    // rm --trash wants -r for a directory like rm does
+    let res = sh.eval_program("rm --trash d").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "rm: cannot remove 'd': Is a directory\n");
+    let res = sh.eval_program("rm -r --trash d").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(fs::read(home.join("Trash/files/d/inner")).unwrap(), b"x");
+
+    fs::write(root.join("work/a.txt"), b"second").unwrap();
+    let res = sh.eval_program("rm --trash a.txt").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        fs::read(home.join("Trash/files/a.txt.2")).unwrap(),
+        b"second"
+    );
+
+    let res = sh.eval_program("trash list").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let ours: Vec<&str> = res
+        .stdout
+        .lines()
+        .filter(|line| line.contains(&*root.to_string_lossy()))
+        .collect();
+    assert_eq!(ours.len(), 3, "{}", res.stdout);
+    assert!(ours
+        .iter()
+        .all(|line| line.ends_with("/work/a.txt") || line.ends_with("/work/d")));
+
+    let res = sh
+        .eval_program("trash list | where type -eq dir | select name")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res.stdout.lines().any(|line| line == "d"), "{}", res.stdout);
+
+    // By path the newest comes back, by name the one asked for
+    let res = sh.eval_program("trash restore -v a.txt").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        format!("restored '{}/work/a.txt'\n", root.display())
+    );
+    assert_eq!(fs::read(root.join("work/a.txt")).unwrap(), b"second");
+    assert!(!home.join("Trash/info/a.txt.2.trashinfo").exists());
+
+    let res = sh.eval_program("trash restore a.txt").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "trash: cannot restore 'a.txt': File exists\n");
+    fs::remove_file(root.join("work/a.txt")).unwrap();
+    let res = sh.eval_program("trash restore a.txt d").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(fs::read(root.join("work/a.txt")).unwrap(), b"first");
+    assert_eq!(fs::read(root.join("work/d/inner")).unwrap(), b"x");
+
+    let res = sh.eval_program("trash restore a.txt").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "trash: a.txt: not in the trash\n");
+
+    let res = sh.eval_program("trash missing").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "trash: cannot move 'missing' to the trash: No such file or directory\n"
+    );
+    assert_eq!(sh.eval_program("trash -f missing").unwrap().exit_code, 0);
+}
@@ -0,0 +1,56 @@
+mod common;
+use common::shell_in;
+use std::fs;
+
+#[test]
+fn draws_filtered_trees() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("target")).unwrap();
+    fs::write(root.join("a.txt"), "a").unwrap();
+    fs::write(root.join("debug.log"), "log").unwrap();
+    fs::write(root.join("src/lib.rs"), "lib").unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("target/out.bin"), "").unwrap();
+    fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+
+    let mut sh = shell_in(root);
+    // Print names without colors even when the tests run on a terminal
+    sh.context_mut().interactive = false;
+
+    let res = sh.eval_program("tree").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(
+        res.stdout,
+        ".\n\
+         ├── a.txt\n\
+         ├── debug.log\n\
+         ├── src\n\
+         │   ├── lib.rs\n\
+         │   └── main.rs\n\
+         └── target\n    \
+             └── out.bin\n\
+         \n\
+         2 directories, 5 files\n"
+    );
+
+    let res = sh.eval_program("tree --gitignore --noreport").unwrap();
+    assert_eq!(
+        res.stdout,
+        ".\n├── a.txt\n└── src\n    ├── lib.rs\n    └── main.rs\n"
+    );
+
+    let res = sh.eval_program("tree -d -L 1 -I target").unwrap();
+    assert_eq!(res.stdout, ".\n└── src\n\n1 directory\n");
+
+    let res = sh.eval_program("tree -s -P '*.rs' src").unwrap();
+    assert_eq!(
+        res.stdout,
+        "src\n├── [ 3]  lib.rs\n└── [12]  main.rs\n\n0 directories, 2 files\n"
+    );
+
+    let res = sh.eval_program("tree missing").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "tree: missing: No such file or directory\n");
+}
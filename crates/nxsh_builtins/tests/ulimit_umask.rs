@@ -0,0 +1,42 @@
+mod common;
+use common::shell;
+
+#[test]
+fn umask_sets_octal_and_symbolic_masks() {
+    let mut sh = shell();
+    let res = sh.eval_program("umask 027; umask; umask -S").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "0027\nu=rwx,g=rx,o=\n");
+
+    let res = sh.eval_program("umask g-x,o=r; umask -p").unwrap();
+    assert_eq!(res.stdout, "umask 0033\n");
+
+    let res = sh.eval_program("umask 0999").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "umask: 0999: octal number out of range\n");
+
+    sh.eval_program("umask 022").unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn ulimit_lowers_and_restores_a_soft_limit() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("ulimit -S -c 0; ulimit -c; ulimit -S -c hard")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "0\n");
+
+    let res = sh.eval_program("ulimit -a").unwrap();
+    assert!(res
+        .stdout
+        .contains("open files                          (-n) "));
+
+    let res = sh.eval_program("ulimit -n lots").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "ulimit: lots: invalid number\n");
+
+    let res = sh.eval_program("ulimit -k").unwrap();
+    assert_eq!(res.exit_code, 2);
+}
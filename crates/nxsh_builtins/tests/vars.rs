@@ -20,7 +20,7 @@ fn let_plus_equal() {
 fn declare_assoc() {
     let ctx = ShellContext::new();
     declare_cli(&["-A".into(), "myarr".into()], &ctx).unwrap();
-    assert_eq!(ctx.get_var("myarr").unwrap(), "__assoc_array__");
+    assert!(ctx.get_array("myarr").is_some_and(|a| a.is_associative()));
 }
 
 #[test]
@@ -0,0 +1,27 @@
+mod common;
+use common::{shell, shell_in};
+
+#[cfg(unix)]
+#[test]
+fn reruns_until_the_output_changes() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut sh = shell_in(dir.path());
+    // Write frames as text even when the tests run on a terminal
+    sh.context_mut().interactive = false;
+
+    let res = sh
+        .eval_program("watch -t -g -n 0.1 'echo run >> log; cat log'")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "run\nrun\nrun\n");
+}
+
+#[test]
+fn rejects_a_missing_command() {
+    let mut sh = shell();
+
+    let res = sh.eval_program("watch -n 1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "watch: no command given\n");
+}
@@ -0,0 +1,272 @@
+mod common;
+use common::shell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// A server on a local port serving `files` by path, taking Range
+/// requests, and logging the head of each request
+fn serve(files: &[(&str, &[u8])]) -> (u16, Log) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let files: Arc<HashMap<String, Vec<u8>>> = Arc::new(
+        files
+            .iter()
+            .map(|(path, body)| (path.to_string(), body.to_vec()))
+            .collect(),
+    );
+    let log: Log = Arc::default();
+    let requests = log.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let files = files.clone();
+            let requests = requests.clone();
+            thread::spawn(move || answer(stream.unwrap(), &files, &requests));
+        }
+    });
+    (port, log)
+}
+
+fn answer(stream: TcpStream, files: &HashMap<String, Vec<u8>>, log: &Log) {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    log.lock().unwrap().push(head.clone());
+    let path = head.split(' ').nth(1).unwrap_or("/").to_string();
+    let range = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Range: bytes="))
+        .map(str::to_string);
+    let mut stream = reader.into_inner();
+    let Some(body) = files.get(&path) else {
+        let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        return;
+    };
+    let kind = if path.ends_with(".html") || path.ends_with('/') {
+        "text/html"
+    } else {
+        "text/plain"
+    };
+    let (status, part) = match range {
+        Some(range) => {
+            let (start, end) = range.split_once('-').unwrap();
+            let start: usize = start.parse().unwrap();
+            let end: usize = end.parse().unwrap_or(body.len() - 1);
+            if start >= body.len() {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\n\r\n");
+                return;
+            }
+            (
+                "206 Partial Content",
+                &body[start..=end.min(body.len() - 1)],
+            )
+        }
+        None => ("200 OK", &body[..]),
+    };
+    let _ = stream.write_all(
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {kind}\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\n\r\n",
+            part.len()
+        )
+        .as_bytes(),
+    );
+    let _ = stream.write_all(part);
+}
+
+#[test]
+fn downloads_files_without_clobbering() {
+    let (port, _log) = serve(&[("/docs/a.txt", b"hello world")]);
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "wget -P {} 127.0.0.1:{port}/docs/a.txt",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "");
+    assert!(res.stderr.contains(
+        "HTTP request sent, awaiting response... 200 OK\nLength: 11 (11) [text/plain]\n"
+    ));
+    assert!(
+        res.stderr.contains("a.txt' saved [11/11]"),
+        "{}",
+        res.stderr
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+        "hello world"
+    );
+
+    let res = sh
+        .eval_program(&format!(
+            "wget -q -P {} 127.0.0.1:{port}/docs/a.txt",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stderr, "");
+    assert!(dir.path().join("a.txt.1").exists());
+
+    let res = sh
+        .eval_program(&format!("wget -q -O - 127.0.0.1:{port}/docs/a.txt"))
+        .unwrap();
+    assert_eq!(res.stdout, "hello world");
+}
+
+#[test]
+fn continues_a_partial_file() {
+    let (port, log) = serve(&[("/a.txt", b"hello world")]);
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello ").unwrap();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "wget -c -P {} 127.0.0.1:{port}/a.txt",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res
+        .stderr
+        .contains("Length: 11 (11), 5 (5) remaining [text/plain]"));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+        "hello world"
+    );
+    assert!(log.lock().unwrap()[0].contains("Range: bytes=6-\r\n"));
+
+    let res = sh
+        .eval_program(&format!(
+            "wget -c -P {} 127.0.0.1:{port}/a.txt",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(res
+        .stderr
+        .contains("The file is already fully retrieved; nothing to do."));
+}
+
+#[test]
+fn mirrors_links_within_robots_and_the_parent() {
+    let index = br#"<a href="page.html">p</a> <a href="/site/secret/x.html">s</a>
+        <a href="../outside.html">o</a> <a href="sub/">d</a> <a href="http://other.invalid/">x</a>"#;
+    let (port, _log) = serve(&[
+        ("/robots.txt", b"User-agent: *\nDisallow: /site/secret\n"),
+        ("/site/index.html", index),
+        ("/site/page.html", b"<a href=\"index.html\">back</a>"),
+        ("/site/secret/x.html", b"secret"),
+        ("/site/sub/", b"sub index"),
+        ("/outside.html", b"outside"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "wget -nv -r -np -nH -P {} 127.0.0.1:{port}/site/index.html",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let root = dir.path().join("site");
+    assert!(root.join("index.html").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("page.html")).unwrap(),
+        "<a href=\"index.html\">back</a>"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("sub/index.html")).unwrap(),
+        "sub index"
+    );
+    assert!(!root.join("secret").exists());
+    assert!(!dir.path().join("outside.html").exists());
+    assert!(res.stderr.contains("Downloaded: 3 files"), "{}", res.stderr);
+}
+
+#[test]
+fn splits_a_file_into_segments() {
+    let content: Vec<u8> = (0..30_000u32).map(|n| (n % 251) as u8).collect();
+    let (port, log) = serve(&[("/big.bin", &content)]);
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("big.bin");
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!(
+            "wget -q --segments 3 -O {} 127.0.0.1:{port}/big.bin",
+            file.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(std::fs::read(&file).unwrap(), content);
+    let mut ranges: Vec<String> = log
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|head| {
+            head.lines()
+                .find_map(|line| line.strip_prefix("Range: "))
+                .map(str::to_string)
+        })
+        .collect();
+    ranges.sort();
+    assert_eq!(
+        ranges,
+        vec!["bytes=0-9999", "bytes=10000-19999", "bytes=20000-29999"]
+    );
+}
+
+#[test]
+fn limits_the_rate() {
+    let content = vec![b'x'; 3000];
+    let (port, _log) = serve(&[("/slow.txt", &content)]);
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh
+        .eval_program(&format!(
+            "wget -q --limit-rate=1k -O - 127.0.0.1:{port}/slow.txt"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout.len(), 3000);
+    assert!(started.elapsed() >= Duration::from_millis(1500));
+}
+
+#[test]
+fn reports_errors_with_wget_statuses() {
+    let (port, _log) = serve(&[]);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("wget -nv -O - 127.0.0.1:{port}/missing"))
+        .unwrap();
+    assert_eq!(res.exit_code, 8);
+    assert!(
+        res.stderr.ends_with(" ERROR 404: Not Found.\n"),
+        "{}",
+        res.stderr
+    );
+
+    let res = sh.eval_program("wget --bogus x").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert_eq!(
+        res.stderr,
+        "wget: unrecognized option '--bogus'\nUsage: wget [OPTION]... [URL]...\n"
+    );
+
+    let res = sh.eval_program("wget").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.starts_with("wget: missing URL\n"));
+}
@@ -0,0 +1,18 @@
+mod common;
+use common::shell_with_input;
+
+#[cfg(unix)]
+#[test]
+fn batches_items_into_command_lines() {
+    let mut sh = shell_with_input("one two\nthree 'four five'\n");
+    let res = sh.eval_program("xargs -n 2 echo item:").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "item: one two\nitem: three four five\n");
+
+    let mut sh = shell_with_input("a\nb\n");
+    let res = sh
+        .eval_program("xargs -I {} sh -c 'echo [{}]; exit 2'")
+        .unwrap();
+    assert_eq!(res.exit_code, 123);
+    assert_eq!(res.stdout, "[a]\n[b]\n");
+}
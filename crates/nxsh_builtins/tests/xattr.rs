@@ -0,0 +1,132 @@
+#![cfg(target_os = "linux")]
+mod common;
+use common::shell;
+use nxsh_core::Shell;
+
+/// A directory the shell works in, with a file, or `None` when its file
+/// system keeps no user attributes
+fn setup(sh: &mut Shell) -> Option<tempfile::TempDir> {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes");
+    std::fs::write(&file, "contents").unwrap();
+    nxsh_hal::fs::list_xattrs(&file, true).ok()?;
+    sh.context_mut().cwd = dir.path().to_path_buf();
+    Some(dir)
+}
+
+#[test]
+fn setfattr_values_come_back_from_getfattr() {
+    let mut sh = shell();
+    let Some(_dir) = setup(&mut sh) else {
+        return;
+    };
+    let res = sh
+        .eval_program("setfattr -n user.comment -v draft notes; setfattr -n user.flag notes")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+    let res = sh.eval_program("getfattr notes").unwrap();
+    assert_eq!(res.stdout, "# file: notes\nuser.comment\nuser.flag\n\n");
+    let res = sh.eval_program("getfattr -d notes").unwrap();
+    assert_eq!(
+        res.stdout,
+        "# file: notes\nuser.comment=\"draft\"\nuser.flag=\"\"\n\n"
+    );
+    let res = sh
+        .eval_program("getfattr -n user.comment -e hex notes")
+        .unwrap();
+    assert_eq!(res.stdout, "# file: notes\nuser.comment=0x6472616674\n\n");
+    let res = sh
+        .eval_program("getfattr --only-values -n user.comment notes")
+        .unwrap();
+    assert_eq!(res.stdout, "draft");
+
+    // Escapes in quoted values, printed back the same way
+    sh.eval_program(r#"setfattr -n user.comment -v "tab\011end" notes"#)
+        .unwrap();
+    let res = sh.eval_program("getfattr -n user.comment notes").unwrap();
+    assert_eq!(
+        res.stdout,
+        "# file: notes\nuser.comment=\"tab\\011end\"\n\n"
+    );
+    let res = sh
+        .eval_program("getfattr -n user.comment -e base64 notes")
+        .unwrap();
+    assert_eq!(res.stdout, "# file: notes\nuser.comment=0sdGFiCWVuZA==\n\n");
+}
+
+#[test]
+fn removed_and_missing_attributes_are_errors() {
+    let mut sh = shell();
+    let Some(_dir) = setup(&mut sh) else {
+        return;
+    };
+    sh.eval_program("setfattr -n user.comment -v draft notes")
+        .unwrap();
+    let res = sh.eval_program("setfattr -x user.comment notes").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh.eval_program("getfattr -d notes").unwrap();
+    assert_eq!(res.stdout, "");
+
+    let res = sh.eval_program("setfattr -x user.comment notes").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "setfattr: notes: user.comment: No such attribute\n"
+    );
+    let res = sh.eval_program("getfattr -n user.comment notes").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "getfattr: notes: user.comment: No such attribute\n"
+    );
+    let res = sh.eval_program("getfattr missing").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "getfattr: missing: No such file or directory\n");
+}
+
+#[test]
+fn patterns_pick_the_names_and_absolute_names_lose_the_slash() {
+    let mut sh = shell();
+    let Some(dir) = setup(&mut sh) else {
+        return;
+    };
+    sh.eval_program("setfattr -n user.a -v 1 notes; setfattr -n user.b -v 2 notes")
+        .unwrap();
+    let res = sh.eval_program("getfattr -m user.b notes").unwrap();
+    assert_eq!(res.stdout, "# file: notes\nuser.b\n\n");
+
+    let absolute = dir.path().join("notes").display().to_string();
+    let res = sh
+        .eval_program(&format!("getfattr -m user.a {absolute}"))
+        .unwrap();
+    assert_eq!(
+        res.stdout,
+        format!("# file: {}\nuser.a\n\n", &absolute[1..])
+    );
+    assert_eq!(
+        res.stderr,
+        "getfattr: Removing leading '/' from absolute path names\n"
+    );
+    let res = sh
+        .eval_program(&format!("getfattr --absolute-names -m user.a {absolute}"))
+        .unwrap();
+    assert_eq!(res.stdout, format!("# file: {absolute}\nuser.a\n\n"));
+}
+
+#[test]
+fn usage_errors_exit_2() {
+    let mut sh = shell();
+    for command in [
+        "getfattr",
+        "getfattr -e rot13 notes",
+        "getfattr -q notes",
+        "setfattr notes",
+        "setfattr -n user.a",
+        "setfattr -n user.a -x user.b notes",
+    ] {
+        let res = sh.eval_program(command).unwrap();
+        assert_eq!(res.exit_code, 2, "{command}");
+        assert!(res.stderr.contains("usage:"), "{command}");
+    }
+}
@@ -3,9 +3,7 @@ use clap::Parser;
 use std::io::{self, IsTerminal};
 use std::time::Instant;
 
-// Add required imports for enhanced functionality
-extern crate chrono;
-extern crate whoami;
+mod startup;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -262,21 +260,107 @@ struct CliArgs {
     #[arg(long)]
     theme: Option<String>,
 
+    /// Exit immediately when a command fails (set -e)
+    #[arg(short = 'e')]
+    errexit: bool,
+
+    /// Treat unset variables as an error when expanding (set -u)
+    #[arg(short = 'u')]
+    nounset: bool,
+
+    /// Print commands and their arguments as they are executed (set -x)
+    #[arg(short = 'x')]
+    xtrace: bool,
+
+    /// Enable a named shell option, e.g. `-o pipefail` (set -o)
+    #[arg(short = 'o', value_name = "OPTION")]
+    option: Vec<String>,
+
+    /// Act as a login shell and run ~/.nxsh_profile
+    #[arg(short = 'l', long)]
+    login: bool,
+
+    /// Do not run /etc/nxshrc and ~/.nxshrc in interactive shells
+    #[arg(long)]
+    norc: bool,
+
+    /// Do not run ~/.nxsh_profile in login shells
+    #[arg(long)]
+    noprofile: bool,
+
+    /// Print the MIR of each command before and after optimization; selects
+    /// the MIR engine unless NXSH_EXEC_STRATEGY is set
+    #[arg(long)]
+    mir_dump: bool,
+
+    /// Compile a script to MIR into the script cache and exit
+    #[arg(long, value_name = "SCRIPT")]
+    compile: Option<String>,
+
     /// Remaining arguments (treated as a command to execute)
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     args: Vec<String>,
 }
 
 #[cfg(not(feature = "cli-args"))]
-fn parse_simple_args() -> (bool, bool, Option<String>, bool, Option<String>) {
-    let args: Vec<String> = std::env::args().collect();
+fn parse_simple_args() -> (
+    bool,
+    bool,
+    Option<String>,
+    bool,
+    Option<Vec<String>>,
+    Vec<String>,
+    startup::StartupOptions,
+) {
+    let mut args: Vec<String> = std::env::args().collect();
     let mut busybox = false;
     let mut interactive = false;
     let mut command = None;
     let mut debug = false;
-    let script_file = None; // Always None for simple args
+    let mut script_file = None;
+    let mut startup = startup::StartupOptions::default();
+
+    // Leading shell option flags (`-eux`, `-o pipefail`, `--login`) apply
+    // before the command
+    let mut shell_options = Vec::new();
+    while args.len() > 1 {
+        let flag = args[1].clone();
+        if startup.apply_flag(&flag) {
+            args.remove(1);
+        } else if flag == "-o" && args.len() > 2 {
+            shell_options.push(args[2].clone());
+            args.drain(1..3);
+        } else if flag == "--mir-dump" {
+            enable_mir_dump();
+            args.remove(1);
+        } else if flag == "--compile" && args.len() > 2 {
+            std::process::exit(compile_script(&args[2]));
+        } else if flag.len() > 1
+            && flag.starts_with('-')
+            && flag[1..].chars().all(|c| matches!(c, 'e' | 'u' | 'x'))
+        {
+            shell_options.extend(flag[1..].chars().map(|c| c.to_string()));
+            args.remove(1);
+        } else {
+            break;
+        }
+    }
+
+    // `nxsh script.sh a b c`: run the script with `$1..$N` = a b c
+    if args.len() > 1 && std::path::Path::new(&args[1]).is_file() {
+        script_file = Some(args[1..].to_vec());
+        return (
+            busybox,
+            interactive,
+            command,
+            debug,
+            script_file,
+            shell_options,
+            startup,
+        );
+    }
 
-    // If we have arguments, they represent a command to execute
+    // Otherwise the arguments represent a command to execute
     // Format: nxsh.exe command arg1 arg2 ...
     // This should be treated as: -c "command arg1 arg2 ..."
     if args.len() > 1 {
@@ -284,10 +368,63 @@ fn parse_simple_args() -> (bool, bool, Option<String>, bool, Option<String>) {
         let cmd_parts: Vec<String> = args[1..].to_vec();
         let full_command = cmd_parts.join(" ");
         command = Some(full_command);
-        return (busybox, interactive, command, debug, script_file);
+        return (
+            busybox,
+            interactive,
+            command,
+            debug,
+            script_file,
+            shell_options,
+            startup,
+        );
     }
 
-    (busybox, interactive, command, debug, script_file)
+    (
+        busybox,
+        interactive,
+        command,
+        debug,
+        script_file,
+        shell_options,
+        startup,
+    )
+}
+
+/// `--mir-dump`: the executor prints MIR when `NXSH_MIR_DUMP` is set, and
+/// only programs run by the MIR engine have MIR to print
+fn enable_mir_dump() {
+    std::env::set_var("NXSH_MIR_DUMP", "1");
+    if std::env::var_os("NXSH_EXEC_STRATEGY").is_none() {
+        std::env::set_var("NXSH_EXEC_STRATEGY", "mir");
+    }
+}
+
+/// `--compile`: store the MIR of the script at `path` in the script cache,
+/// so the MIR engine runs it without parsing it again. Returns the exit status
+fn compile_script(path: &str) -> i32 {
+    let Some(cache) = nxsh_core::mir::cache::MirCache::from_env() else {
+        eprintln!("nxsh: --compile: the MIR cache is disabled (NXSH_MIR_CACHE=0)");
+        return 1;
+    };
+    let compiled = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| {
+            let ast = nxsh_parser::ShellCommandParser::new()
+                .parse(&source)
+                .map_err(|e| e.to_string())?;
+            let program = nxsh_core::mir::cache::compile(&ast);
+            cache.store(&source, &program).map_err(|e| e.to_string())
+        });
+    match compiled {
+        Ok(entry) => {
+            println!("{}", entry.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("nxsh: {path}: {e}");
+            1
+        }
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -310,25 +447,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Parse CLI arguments
     #[cfg(not(feature = "cli-args"))]
-    let (busybox, interactive, command, debug, script_file) = parse_simple_args();
+    let (busybox, interactive, command, debug, script_file, shell_options, startup) =
+        parse_simple_args();
 
     #[cfg(feature = "cli-args")]
-    let (busybox, interactive, command, debug, script_file) = {
+    let (busybox, interactive, command, debug, script_file, shell_options, startup) = {
         let args = CliArgs::parse();
+        if args.mir_dump {
+            enable_mir_dump();
+        }
+        if let Some(script) = &args.compile {
+            std::process::exit(compile_script(script));
+        }
+        // `nxsh script.sh a b c`: the first operand names a script file
+        let script_file = (args.command.is_none()
+            && args
+                .args
+                .first()
+                .is_some_and(|a| std::path::Path::new(a).is_file()))
+        .then(|| args.args.clone());
         let command = if args.command.is_some() {
             args.command
+        } else if script_file.is_some() {
+            None
         } else if !args.args.is_empty() {
             // Treat remaining args as a command to execute
             Some(args.args.join(" "))
         } else {
             None
         };
+        let mut shell_options = args.option;
+        for (enabled, name) in [
+            (args.errexit, "errexit"),
+            (args.nounset, "nounset"),
+            (args.xtrace, "xtrace"),
+        ] {
+            if enabled {
+                shell_options.push(name.to_string());
+            }
+        }
         (
             args.busybox,
             args.interactive,
             command,
             args.debug,
-            None::<String>, // No script_file in new structure
+            script_file,
+            shell_options,
+            startup::StartupOptions {
+                login: args.login,
+                norc: args.norc,
+                noprofile: args.noprofile,
+            },
         )
     };
 
@@ -345,7 +514,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Load configuration - use simplified approach for now
-    let config = nxsh_core::Config::default();
+    let config = nxsh_core::Config {
+        shell_options,
+        ..Default::default()
+    };
 
     // Initialize UI system
     #[cfg(feature = "ui")]
@@ -357,6 +529,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize plugin system
     #[cfg(feature = "plugins")]
     let _plugin_manager = nxsh_plugin::PluginManager::new();
+    #[cfg(feature = "plugin-wasi")]
+    nxsh_builtins::plugin::load(&nxsh_builtins::plugin::PluginHost {
+        commands: shell_state.plugin_commands.clone(),
+        hooks: shell_state.hooks.clone(),
+    });
 
     // Initialize parser
     let parser = nxsh_parser::ShellCommandParser::new();
@@ -367,22 +544,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("Startup time: {startup_time:?}");
     }
 
+    // Interactive mode detection - simplified
+    let is_interactive = command.is_none()
+        && script_file.is_none()
+        && (interactive
+            || (!cfg!(feature = "non-interactive-default")
+                && io::stdin().is_terminal()
+                && io::stdout().is_terminal()));
+
+    // Profile and rc scripts run before anything else
+    let startup = startup::StartupOptions {
+        login: startup.login || startup::invoked_as_login(std::env::args().next().as_deref()),
+        ..startup
+    };
+    startup::run_startup_files(startup, is_interactive, &mut shell_state);
+
     // Command execution mode
     if let Some(cmd) = command {
         return run_command(&cmd, &mut shell_state, &parser);
     }
 
     // Script execution mode
-    if let Some(script) = script_file {
+    if let Some(mut argv) = script_file {
+        let script = argv.remove(0);
+        shell_state.positional = nxsh_core::context::PositionalParams::new(script.clone(), argv);
         return run_script(&script, &mut shell_state, &parser);
     }
 
-    // Interactive mode detection - simplified
-    let is_interactive = interactive
-        || (!cfg!(feature = "non-interactive-default")
-            && io::stdin().is_terminal()
-            && io::stdout().is_terminal());
-
     if is_interactive {
         // Start interactive mode
         run_interactive_mode(
@@ -397,6 +585,108 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Build a core shell from persisted state, with the context-aware builtins
+/// from `nxsh_builtins` (trap, ...) registered on its executor
+fn shell_from_state(shell_state: &nxsh_core::ShellState) -> nxsh_core::Shell {
+    let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+    for builtin in nxsh_builtins::shell_builtins() {
+        shell.register_builtin(builtin);
+    }
+    for builtin in nxsh_builtins::structured_builtins() {
+        shell.register_structured_builtin(builtin);
+    }
+    shell.set_table_renderer(nxsh_builtins::structured::render_table);
+    #[cfg(feature = "ui")]
+    shell.set_menu_renderer(nxsh_ui::menu::render);
+    shell
+}
+
+/// Whether the interactive loop runs `name` through the legacy builtin
+/// table. Builtins the shell registers run in it instead, so that they see
+/// and change its state and fire its hooks, as `cd` does `cwd_changed`.
+fn is_legacy_builtin(name: &str) -> bool {
+    static SHELL_BUILTINS: std::sync::OnceLock<std::collections::HashSet<&'static str>> =
+        std::sync::OnceLock::new();
+    let shell_builtins = SHELL_BUILTINS.get_or_init(|| {
+        nxsh_builtins::shell_builtins()
+            .iter()
+            .map(|builtin| builtin.name())
+            .collect()
+    });
+    nxsh_builtins::is_builtin(name) && !shell_builtins.contains(name)
+}
+
+/// Report the background jobs that finished and run the `job_finished` and
+/// `prompt_render` hooks before showing a prompt. With `checkwinsize`,
+/// `COLUMNS` and `LINES` follow the terminal's size first.
+fn before_prompt(
+    shell_state: &mut nxsh_core::ShellState,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    if shell_state.options.checkwinsize {
+        if let Ok((columns, rows)) = crossterm::terminal::size() {
+            let environment = &mut shell_state.environment;
+            environment.insert("COLUMNS".to_string(), columns.to_string());
+            environment.insert("LINES".to_string(), rows.to_string());
+        }
+    }
+    let finished = shell_state
+        .job_manager
+        .lock()
+        .map(|jobs| jobs.get_all_jobs().iter().any(nxsh_core::Job::is_finished))
+        .unwrap_or(false);
+    if !finished && !shell_state.hooks.has(nxsh_core::HookKind::PromptRender) {
+        return Ok(());
+    }
+    let mut shell = shell_from_state(shell_state);
+    let result = shell.before_prompt();
+    *shell_state = shell.into_state();
+    if !result.stdout.is_empty() {
+        write!(std::io::stdout(), "{}", result.stdout)?;
+        std::io::stdout().flush()?;
+    }
+    if !result.stderr.is_empty() {
+        write!(std::io::stderr(), "{}", result.stderr)?;
+        std::io::stderr().flush()?;
+    }
+    Ok(())
+}
+
+/// Expand history references in a line typed at the prompt and record it,
+/// echoing the command a reference expanded to. `None` if expansion failed.
+fn accept_line(shell_state: &mut nxsh_core::ShellState, input: &str) -> Option<String> {
+    let mut shell = shell_from_state(shell_state);
+    let accepted = shell.accept_line(input);
+    *shell_state = shell.into_state();
+    match accepted {
+        Ok(line) => {
+            if line != input {
+                println!("{line}");
+            }
+            Some(line)
+        }
+        Err(message) => {
+            eprintln!("nxsh: {message}");
+            None
+        }
+    }
+}
+
+/// Run the EXIT trap, if one is registered, and print what it wrote
+fn run_exit_trap(shell: &mut nxsh_core::Shell) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let result = shell.run_exit_trap()?;
+    if !result.stdout.is_empty() {
+        write!(std::io::stdout(), "{}", result.stdout)?;
+        std::io::stdout().flush()?;
+    }
+    if !result.stderr.is_empty() {
+        write!(std::io::stderr(), "{}", result.stderr)?;
+        std::io::stderr().flush()?;
+    }
+    Ok(())
+}
+
 fn run_command(
     command: &str,
     shell_state: &mut nxsh_core::ShellState,
@@ -416,7 +706,7 @@ fn run_command(
     if contains_shell_syntax(command) {
         // Parse to AST, evaluate through nxsh_core::Shell to capture stdout/stderr
         let ast = parser.parse(command)?;
-        let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+        let mut shell = shell_from_state(shell_state);
         let result = shell.eval_ast(&ast)?;
         // Print captured outputs explicitly
         use std::io::Write;
@@ -428,6 +718,7 @@ fn run_command(
             write!(std::io::stderr(), "{}", result.stderr)?;
             std::io::stderr().flush()?;
         }
+        run_exit_trap(&mut shell)?;
         *shell_state = shell.into_state();
         if result.exit_code != 0 {
             std::process::exit(result.exit_code);
@@ -462,7 +753,7 @@ fn run_command(
 
     // Fall back to regular parser/AST execution via shell to capture output
     let ast = parser.parse(command)?;
-    let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+    let mut shell = shell_from_state(shell_state);
     let result = shell.eval_ast(&ast)?;
     use std::io::Write;
     if !result.stdout.is_empty() {
@@ -473,6 +764,7 @@ fn run_command(
         write!(std::io::stderr(), "{}", result.stderr)?;
         std::io::stderr().flush()?;
     }
+    run_exit_trap(&mut shell)?;
     *shell_state = shell.into_state();
     if result.exit_code != 0 {
         std::process::exit(result.exit_code);
@@ -486,10 +778,14 @@ fn run_script(
     parser: &nxsh_parser::ShellCommandParser,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let content = std::fs::read_to_string(script_path)?;
-    let ast = parser.parse(&content)?;
     // Evaluate via shell to capture outputs
-    let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
-    let result = shell.eval_ast(&ast)?;
+    let mut shell = shell_from_state(shell_state);
+    // The MIR engine runs the compiled program cached for this source
+    let cache = nxsh_core::mir::cache::MirCache::from_env().filter(|_| shell.uses_mir_engine());
+    let result = match cache {
+        Some(cache) => shell.eval_program_cached(&content, &cache)?,
+        None => shell.eval_ast(&parser.parse(&content)?)?,
+    };
     use std::io::Write;
     if !result.stdout.is_empty() {
         write!(std::io::stdout(), "{}", result.stdout)?;
@@ -499,6 +795,7 @@ fn run_script(
         write!(std::io::stderr(), "{}", result.stderr)?;
         std::io::stderr().flush()?;
     }
+    run_exit_trap(&mut shell)?;
     *shell_state = shell.into_state();
     if result.exit_code != 0 {
         std::process::exit(result.exit_code);
@@ -512,20 +809,71 @@ fn run_interactive_mode(
     parser: &nxsh_parser::ShellCommandParser,
     _ui: &mut nxsh_ui::SimpleUiController,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    shell_state.enable_job_control();
     // Show stylish startup banner
     show_startup_banner();
     println!();
     // Use enhanced ReadLine with tab completion and syntax highlighting
     let mut rl = nxsh_ui::readline::ReadLine::new()?;
+    // Complete builtin and plugin command flags and arguments from their usage lines
+    for builtin in nxsh_builtins::list_builtins() {
+        rl.completer_mut().register_builtin(
+            &builtin.name,
+            &builtin.description,
+            &builtin.usage,
+            &builtin.flags,
+        );
+    }
+    for command in shell_state.plugin_commands.commands() {
+        rl.completer_mut().register_builtin(
+            &command.name,
+            &command.synopsis,
+            &command.completion.usage,
+            &command.completion.flags,
+        );
+    }
+    // Plugins complete the arguments of commands and fill `{plugin:NAME}`
+    // prompt segments, including plugins loaded later in the session
+    rl.completer_mut()
+        .set_plugins(shell_state.plugin_commands.clone());
+    // `!!` and `fc` start from the commands saved by earlier sessions
+    if let Ok(mut history) = shell_state.history.lock() {
+        history.extend(rl.history().entries().map(|entry| entry.command.clone()));
+    }
 
+    // The executor reports each command's exit status and run time back to the editor
+    shell_state.post_command_hooks.add(rl.post_command_hook());
+    let prompt_engine = prompt_engine().with_plugins(shell_state.plugin_commands.clone());
     loop {
-        let prompt = get_enhanced_prompt();
-        let input_line = rl.read_line(&prompt)?; // Handles Tab, arrows, highlight
+        show_job_notices();
+        before_prompt(shell_state)?;
+        let (exit_code, duration) = rl
+            .last_status()
+            .map_or((0, None), |(code, took)| (code, Some(took)));
+        let jobs = shell_state
+            .job_manager
+            .lock()
+            .map(|jobs| jobs.get_running_jobs().len() + jobs.get_stopped_jobs().len())
+            .unwrap_or(0);
+        let prompt = prompt_engine.render(&nxsh_ui::PromptContext {
+            exit_code,
+            duration,
+            jobs,
+            cwd: None,
+        });
+        rl.set_right_prompt(&prompt.right);
+        rl.set_transient_prompt(&prompt.transient);
+        rl.set_vi_mode(shell_state.options.vi_mode);
+        let input_line = rl.read_line(&prompt.left)?; // Handles Tab, arrows, highlight
         let input = input_line.trim();
 
         if input.is_empty() {
             continue;
         }
+        let Some(accepted) = accept_line(shell_state, input) else {
+            continue;
+        };
+        let input = accepted.as_str();
 
         // Handle exit commands
         if input == "exit" || input == "quit" {
@@ -539,15 +887,14 @@ fn run_interactive_mode(
             let args = &parts[1..];
 
             // Prefer built-ins
-            if nxsh_builtins::is_builtin(command_name) {
+            if is_legacy_builtin(command_name) {
                 match nxsh_builtins::execute_builtin(command_name, args) {
                     Ok(exit_code) => {
-                        if exit_code != 0 {
-                            eprintln!("Command exited with code {exit_code}");
-                        }
+                        rl.record_exit_code(exit_code);
                         continue;
                     }
                     Err(e) => {
+                        rl.record_exit_code(1);
                         eprintln!("Error: {e}");
                         continue;
                     }
@@ -558,7 +905,7 @@ fn run_interactive_mode(
         // Fall back to regular parser/AST execution via shell to capture outputs
         match parser.parse(input) {
             Ok(ast) => {
-                let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+                let mut shell = shell_from_state(shell_state);
                 match shell.eval_ast(&ast) {
                     Ok(result) => {
                         use std::io::Write;
@@ -571,21 +918,22 @@ fn run_interactive_mode(
                             std::io::stderr().flush()?;
                         }
                         *shell_state = shell.into_state();
-                        if result.exit_code != 0 {
-                            eprintln!("Command exited with code {}", result.exit_code);
-                        }
+                        rl.record_exit_code(result.exit_code);
                     }
                     Err(e) => {
+                        rl.record_exit_code(1);
                         eprintln!("Error: {e}");
                     }
                 }
             }
             Err(e) => {
+                rl.record_exit_code(2);
                 eprintln!("Parse error: {e}");
             }
         }
     }
 
+    run_exit_trap(&mut shell_from_state(shell_state))?;
     println!("Exiting NexusShell.");
     Ok(())
 }
@@ -595,11 +943,14 @@ fn run_interactive_mode(
     shell_state: &mut nxsh_core::ShellState,
     parser: &nxsh_parser::ShellCommandParser,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    shell_state.enable_job_control();
     // Minimal fallback interactive loop without advanced UI
     println!("NexusShell (UI disabled). Type 'exit' to quit.");
     let mut line = String::new();
     loop {
         use std::io::Write;
+        show_job_notices();
+        before_prompt(shell_state)?;
         print!("nxsh$ ");
         std::io::stdout().flush()?;
         line.clear();
@@ -610,6 +961,10 @@ fn run_interactive_mode(
         if input.is_empty() {
             continue;
         }
+        let Some(accepted) = accept_line(shell_state, input) else {
+            continue;
+        };
+        let input = accepted.as_str();
         if input == "exit" || input == "quit" {
             break;
         }
@@ -618,7 +973,7 @@ fn run_interactive_mode(
         if !parts.is_empty() {
             let command_name = &parts[0];
             let args = &parts[1..];
-            if nxsh_builtins::is_builtin(command_name) {
+            if is_legacy_builtin(command_name) {
                 match nxsh_builtins::execute_builtin(command_name, args) {
                     Ok(code) if code == 0 => {}
                     Ok(code) => eprintln!("Command exited with code {code}"),
@@ -629,7 +984,7 @@ fn run_interactive_mode(
         }
         match parser.parse(input) {
             Ok(ast) => {
-                let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+                let mut shell = shell_from_state(shell_state);
                 match shell.eval_ast(&ast) {
                     Ok(result) => {
                         use std::io::Write;
@@ -652,105 +1007,49 @@ fn run_interactive_mode(
             Err(e) => eprintln!("Parse error: {e}"),
         }
     }
+    run_exit_trap(&mut shell_from_state(shell_state))?;
     println!("Exiting NexusShell.");
     Ok(())
 }
 
-/// Generate enhanced prompt for ReadLine
-fn get_enhanced_prompt() -> String {
-    use std::env;
-    use std::path::PathBuf;
-    // Emergency fallback: simple single-line prompt for terminals that have redraw issues
-    if env::var("NXSH_SIMPLE_PROMPT")
-        .map(|v| v == "1")
-        .unwrap_or(false)
-    {
-        return "$ ".to_string();
+/// Print the results of scheduled jobs that finished since the last prompt;
+/// the first call lets this shell run the jobs
+fn show_job_notices() {
+    #[cfg(feature = "scheduler")]
+    for notice in nxsh_builtins::schedule::poll() {
+        println!("{notice}");
     }
-
-    // Cyberpunk color scheme
-    let cyan = "\x1b[38;2;0;245;255m"; // #00f5ff
-    let purple = "\x1b[38;2;153;69;255m"; // #9945ff
-    let coral = "\x1b[38;2;255;71;87m"; // #ff4757
-    let green = "\x1b[38;2;46;213;115m"; // #2ed573
-    let yellow = "\x1b[38;2;255;190;11m"; // #ffbe0b
-    let bold = "\x1b[1m";
-    let reset = "\x1b[0m";
-
-    // Get username
-    let username = whoami::username();
-
-    // Get hostname (simplified)
-    let hostname = env::var("COMPUTERNAME")
-        .or_else(|_| env::var("HOSTNAME"))
-        .unwrap_or_else(|_| "localhost".to_string());
-
-    // Get current directory
-    let current_dir = env::current_dir()
-        .map(|path| {
-            if let Ok(home) = env::var("HOME").or_else(|_| env::var("USERPROFILE")) {
-                let home_path = PathBuf::from(home);
-                if let Ok(relative) = path.strip_prefix(&home_path) {
-                    format!("~/{}", relative.display())
-                } else {
-                    path.display().to_string()
-                }
-            } else {
-                path.display().to_string()
-            }
-        })
-        .unwrap_or_else(|_| "?".to_string());
-
-    // Get git branch if in git repository
-    let git_branch = get_git_branch();
-    let git_display = if let Some(branch) = git_branch {
-        format!(" {yellow}🌿 {branch}{reset}")
-    } else {
-        String::new()
-    };
-
-    // Get current time
-    let now = chrono::Local::now();
-    let time_str = now.format("%H:%M").to_string();
-
-    // Create multi-line prompt
-    format!("{bold}{cyan}╭─[{green}{username}{reset}{cyan}@{purple}{hostname}{reset} {coral}📁 {}{green}{git_display}{cyan}] {yellow}⏰ {time_str}{reset}\n{cyan}╰─❯{reset} ", 
-        abbreviate_path(&current_dir))
 }
 
-/// Abbreviate long paths for display
-fn abbreviate_path(path: &str) -> String {
-    let max_length = 50;
-    if path.len() <= max_length {
-        path.to_string()
+/// Prompt engine for the interactive loop, with templates from
+/// `NXSH_PROMPT` and `NXSH_RPROMPT` when set
+#[cfg(feature = "ui")]
+fn prompt_engine() -> nxsh_ui::PromptEngine {
+    use nxsh_ui::{PromptEngine, UiConfig};
+    use std::env;
+
+    let mut config = UiConfig::default();
+    // Emergency fallback: simple single-line prompt for terminals that have redraw issues
+    let simple = env::var("NXSH_SIMPLE_PROMPT")
+        .map(|v| v == "1")
+        .unwrap_or(false);
+    if simple {
+        config.prompt_template = "$ ".to_string();
+        config.right_prompt_template.clear();
     } else {
-        let parts: Vec<&str> = path.split('/').collect();
-        if parts.len() > 3 {
-            format!(".../{}", parts[parts.len() - 2..].join("/"))
-        } else {
-            format!("...{}", &path[path.len() - max_length + 3..])
+        if let Ok(template) = env::var("NXSH_PROMPT") {
+            config.prompt_template = template;
         }
-    }
-}
-
-/// Get current git branch
-fn get_git_branch() -> Option<String> {
-    use std::process::Command;
-
-    let output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output();
-
-    if let Ok(output) = output {
-        if output.status.success() {
-            let branch = String::from_utf8_lossy(&output.stdout);
-            let branch = branch.trim();
-            if !branch.is_empty() && branch != "HEAD" {
-                return Some(branch.to_string());
-            }
+        if let Ok(template) = env::var("NXSH_RPROMPT") {
+            config.right_prompt_template = template;
         }
     }
-    None
+    let theme = nxsh_ui::get_theme(&config.theme_name).unwrap_or_default();
+    let engine = PromptEngine::from_ui_config(&config).unwrap_or_else(|e| {
+        eprintln!("nxsh: prompt: {e}");
+        PromptEngine::default()
+    });
+    engine.with_theme(&theme).with_color(!simple)
 }
 
 fn run_non_interactive_mode(
@@ -797,7 +1096,7 @@ fn run_non_interactive_mode(
         // Fall back to regular parser/AST execution via shell to capture outputs
         match parser.parse(line) {
             Ok(ast) => {
-                let mut shell = nxsh_core::Shell::from_state(shell_state.clone());
+                let mut shell = shell_from_state(shell_state);
                 match shell.eval_ast(&ast) {
                     Ok(result) => {
                         use std::io::Write;
@@ -825,6 +1124,7 @@ fn run_non_interactive_mode(
         }
     }
 
+    run_exit_trap(&mut shell_from_state(shell_state))?;
     Ok(())
 }
 
@@ -0,0 +1,162 @@
+//! Startup scripts run before the first command.
+//!
+//! Login shells (`-l`, `--login`, or an argv[0] starting with `-` as set by
+//! `login(1)`) run `~/.nxsh_profile`. Interactive shells then run
+//! `/etc/nxshrc` followed by `~/.nxshrc`. `--noprofile` and `--norc` skip
+//! the respective group. Missing files are skipped silently; parse and
+//! runtime errors are reported and startup continues with the next file.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// System-wide rc file for interactive shells
+const SYSTEM_RC: &str = "/etc/nxshrc";
+/// Per-user rc file for interactive shells, relative to `$HOME`
+const USER_RC: &str = ".nxshrc";
+/// Per-user profile for login shells, relative to `$HOME`
+const USER_PROFILE: &str = ".nxsh_profile";
+
+/// Which startup scripts to run, from the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StartupOptions {
+    /// Run as a login shell
+    pub login: bool,
+    /// Skip the rc files
+    pub norc: bool,
+    /// Skip the login profile
+    pub noprofile: bool,
+}
+
+impl StartupOptions {
+    /// Apply one command-line flag; false if `flag` is not a startup flag.
+    /// Used by the argument parser for builds without clap.
+    #[cfg_attr(feature = "cli-args", allow(dead_code))]
+    pub fn apply_flag(&mut self, flag: &str) -> bool {
+        match flag {
+            "-l" | "--login" => self.login = true,
+            "--norc" => self.norc = true,
+            "--noprofile" => self.noprofile = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Whether the program was started as a login shell by its name, the way
+/// `login(1)` and `su -` start shells (`-nxsh`)
+pub fn invoked_as_login(argv0: Option<&str>) -> bool {
+    argv0.is_some_and(|name| name.starts_with('-'))
+}
+
+/// Startup files to run, in order, for a shell with the given mode
+pub fn startup_files(
+    options: StartupOptions,
+    interactive: bool,
+    home: Option<&Path>,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if options.login && !options.noprofile {
+        files.extend(home.map(|home| home.join(USER_PROFILE)));
+    }
+    if interactive && !options.norc {
+        files.push(PathBuf::from(SYSTEM_RC));
+        files.extend(home.map(|home| home.join(USER_RC)));
+    }
+    files
+}
+
+/// Run the startup files for this shell against `shell_state`
+pub fn run_startup_files(
+    options: StartupOptions,
+    interactive: bool,
+    shell_state: &mut nxsh_core::ShellState,
+) {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    for path in startup_files(options, interactive, home.as_deref()) {
+        run_startup_file(&path, shell_state);
+    }
+}
+
+/// Run one startup file in the shell's own state, so its variables,
+/// options, traps and `cd` carry over into the session
+fn run_startup_file(path: &Path, shell_state: &mut nxsh_core::ShellState) {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            eprintln!("nxsh: {}: {e}", path.display());
+            return;
+        }
+    };
+    if source.trim().is_empty() {
+        return;
+    }
+    // Parse errors come back rendered by the parser's `highlight_error`
+    let ast = match nxsh_parser::parse(&source) {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("nxsh: {}: {e}", path.display());
+            return;
+        }
+    };
+
+    let mut shell = crate::shell_from_state(shell_state);
+    match shell.eval_ast(&ast) {
+        Ok(result) => {
+            let _ = write!(std::io::stdout(), "{}", result.stdout);
+            let _ = std::io::stdout().flush();
+            let _ = write!(std::io::stderr(), "{}", result.stderr);
+        }
+        Err(e) => eprintln!("nxsh: {}: {e}", path.display()),
+    }
+    *shell_state = shell.into_state();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_detection_and_flags() {
+        assert!(invoked_as_login(Some("-nxsh")));
+        assert!(!invoked_as_login(Some("/usr/bin/nxsh")));
+        assert!(!invoked_as_login(None));
+
+        let mut options = StartupOptions::default();
+        assert!(options.apply_flag("--login"));
+        assert!(options.apply_flag("--norc"));
+        assert!(!options.apply_flag("-e"));
+        assert!(options.login && options.norc && !options.noprofile);
+    }
+
+    #[test]
+    fn files_follow_mode_and_escape_hatches() {
+        let home = Path::new("/home/u");
+        let login = StartupOptions {
+            login: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            startup_files(login, true, Some(home)),
+            vec![
+                home.join(".nxsh_profile"),
+                PathBuf::from("/etc/nxshrc"),
+                home.join(".nxshrc"),
+            ]
+        );
+        assert_eq!(
+            startup_files(login, false, Some(home)),
+            vec![home.join(".nxsh_profile")]
+        );
+        let quiet = StartupOptions {
+            login: true,
+            norc: true,
+            noprofile: true,
+        };
+        assert!(startup_files(quiet, true, Some(home)).is_empty());
+        assert_eq!(
+            startup_files(StartupOptions::default(), true, None),
+            vec![PathBuf::from("/etc/nxshrc")]
+        );
+    }
+}
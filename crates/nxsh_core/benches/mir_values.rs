@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nxsh_core::mir::{lower::Lowerer, MirExecutor, MirValue};
+use nxsh_parser::Parser;
+
+/// Values as the VM held them before strings, arrays and objects were
+/// shared: every clone copies the text and the elements
+#[derive(Clone)]
+enum OwnedValue {
+    String(String),
+    Array(Vec<OwnedValue>),
+}
+
+impl OwnedValue {
+    fn len(&self) -> usize {
+        match self {
+            OwnedValue::String(s) => s.len(),
+            OwnedValue::Array(items) => items.len(),
+        }
+    }
+}
+
+/// Cloning is what every register read does, so this compares the old deep
+/// copies ("owned") with reference-counted values ("shared")
+fn bench_value_clone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mir_value_clone");
+
+    let text = "the quick brown fox jumps over the lazy dog ".repeat(100);
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    let owned_string = OwnedValue::String(text.clone());
+    let shared_string = MirValue::string(text.as_str());
+    group.bench_function("owned_string", |b| {
+        b.iter(|| black_box(owned_string.clone()).len())
+    });
+    group.bench_function("shared_string", |b| {
+        b.iter(|| black_box(shared_string.clone()))
+    });
+
+    let owned_array = OwnedValue::Array(
+        words
+            .iter()
+            .map(|w| OwnedValue::String(w.to_string()))
+            .collect(),
+    );
+    let shared_array = MirValue::array(words.iter().map(|w| MirValue::string(*w)).collect());
+    group.bench_function("owned_array", |b| {
+        b.iter(|| black_box(owned_array.clone()).len())
+    });
+    group.bench_function("shared_array", |b| {
+        b.iter(|| black_box(shared_array.clone()))
+    });
+
+    group.finish();
+}
+
+/// A script that mostly moves text around: its run time was dominated by
+/// string copies before values were shared
+fn bench_text_script(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mir_text_script");
+
+    let script = r#"
+        words="alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu"
+        text="$words $words $words $words $words $words $words $words"
+        for round in 1 2 3 4 5 6 7 8 9 10; do
+            for word in $text; do
+                line=$text
+                last="$word"
+                copy=$line
+            done
+        done
+    "#;
+    let parser = Parser::new();
+    let ast = parser.parse(script).expect("parse");
+    let program = Lowerer::new().lower_program(&ast);
+
+    group.bench_function("execute_main", |b| {
+        b.iter(|| {
+            let mut exec = MirExecutor::new();
+            black_box(exec.execute_main(&program).expect("exec"))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_value_clone, bench_text_script);
+criterion_main!(benches);
@@ -2,12 +2,18 @@
 //!
 //! このモジュールは、cron風の定期実行、at風の時間指定実行、
 //! 依存関係管理、優先度制御を含む高度なジョブスケジューリング機能を提供します。
+//!
+//! ジョブは [`SchedulerConfig::shell`] で指定したシェルで実行され、
+//! [`SchedulerConfig::state_file`] を指定するとジョブ一覧がJSONとして保存されます。
+//! 他のプロセスがそのファイルを書き換えた場合は次の確認時に読み込み直します。
 
-use crate::compat::Result;
+use crate::compat::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::{Ordering as CmpOrdering, Reverse},
-    collections::{BinaryHeap, HashMap, VecDeque},
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    process::Stdio,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
@@ -41,19 +47,29 @@ pub struct SchedulerConfig {
     pub enable_priority_queue: bool,
     /// ワーカースレッド数 (0 なら自動検出)
     pub num_workers: usize,
+    /// ジョブを実行するシェル（プログラムと、コマンド行の前に渡す引数）
+    #[serde(default = "default_shell")]
+    pub shell: Vec<String>,
+    /// ジョブ一覧の保存先（`None` なら保存しない）
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
 }
 
 /// 高度なジョブスケジューラー
-#[allow(dead_code)] // 高度なスケジューラは未配線
+#[allow(dead_code)] // 統計用のフィールドは一部未使用
 pub struct AdvancedJobScheduler {
     config: SchedulerConfig,
     jobs: Arc<AsyncRwLock<HashMap<String, ScheduledJob>>>,
     queue: Arc<AsyncRwLock<BinaryHeap<Reverse<QueuedJob>>>>,
     running_jobs: Arc<AsyncRwLock<HashMap<String, RunningJob>>>,
     job_history: Arc<AsyncRwLock<VecDeque<JobHistoryEntry>>>,
+    /// 通知待ちの実行結果
+    notifications: Arc<Mutex<VecDeque<JobNotification>>>,
+    /// ジョブ一覧の保存先
+    state: Arc<StateFile>,
     semaphore: Arc<Semaphore>,
     shutdown_signal: Arc<AtomicBool>,
-    job_counter: AtomicU64,
+    job_counter: Arc<AtomicU64>,
     scheduler_handle: Option<JoinHandle<()>>,
     /// Work-stealing workers (local deques)
     workers: Vec<Worker>,
@@ -266,6 +282,99 @@ pub struct JobStatistics {
     pub top_commands: Vec<(String, u64)>,
 }
 
+/// 通知設定に従って届けるジョブの実行結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobNotification {
+    /// ジョブID
+    pub job_id: String,
+    /// ジョブ名
+    pub name: String,
+    /// 実行したコマンド
+    pub command: String,
+    /// 通知チャンネル
+    pub channels: Vec<String>,
+    /// カスタムメッセージ
+    pub custom_message: Option<String>,
+    /// 実行終了時刻
+    pub finished_at: SystemTime,
+    /// 実行結果
+    pub result: JobExecutionResult,
+}
+
+/// ジョブ一覧を保存するファイルと、最後に読み書きした時点の更新時刻
+#[derive(Debug)]
+struct StateFile {
+    path: Option<PathBuf>,
+    stamp: Mutex<Option<SystemTime>>,
+}
+
+impl StateFile {
+    fn modified(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// 最後に読み書きしてから他のプロセスが書き換えたか
+    fn changed(&self) -> bool {
+        self.path.is_some()
+            && self
+                .stamp
+                .lock()
+                .map(|stamp| *stamp != self.modified())
+                .unwrap_or(false)
+    }
+
+    /// 保存されたジョブ（保存先がなければ `None`、ファイルがなければ空）
+    fn load(&self) -> Result<Option<Vec<ScheduledJob>>> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let jobs = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| failed(path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(failed(path, e)),
+        };
+        if let Ok(mut stamp) = self.stamp.lock() {
+            *stamp = self.modified();
+        }
+        Ok(Some(jobs))
+    }
+
+    /// ジョブ一覧を書き出す（一時ファイルに書いてから置き換える）
+    async fn save(&self, jobs: &AsyncRwLock<HashMap<String, ScheduledJob>>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut list: Vec<ScheduledJob> = jobs.read().await.values().cloned().collect();
+        list.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        let text = serde_json::to_string_pretty(&list).map_err(|e| failed(path, e))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| failed(dir, e))?;
+        }
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, text).map_err(|e| failed(&temporary, e))?;
+        std::fs::rename(&temporary, path).map_err(|e| failed(path, e))?;
+        if let Ok(mut stamp) = self.stamp.lock() {
+            *stamp = self.modified();
+        }
+        Ok(())
+    }
+}
+
+/// `path: error` の形のエラー
+fn failed(path: &std::path::Path, error: impl std::fmt::Display) -> crate::compat::Error {
+    anyhow(format!("{}: {error}", path.display()))
+}
+
+fn default_shell() -> Vec<String> {
+    let shell: &[&str] = if cfg!(windows) {
+        &["cmd", "/C"]
+    } else {
+        &["sh", "-c"]
+    };
+    shell.iter().map(|s| s.to_string()).collect()
+}
+
 impl Default for SchedulerConfig {
     fn default() -> Self {
         Self {
@@ -277,6 +386,37 @@ impl Default for SchedulerConfig {
             default_timeout_secs: 3600, // 1時間
             enable_priority_queue: true,
             num_workers: 0,
+            shell: default_shell(),
+            state_file: None,
+        }
+    }
+}
+
+impl JobSchedule {
+    /// 次の実行予定時刻（イベント依存なら `None`）
+    pub fn next_run(&self) -> Option<SystemTime> {
+        match self {
+            JobSchedule::Once { run_at } => Some(*run_at),
+            JobSchedule::Recurring { next_run, .. } | JobSchedule::Interval { next_run, .. } => {
+                Some(*next_run)
+            }
+            JobSchedule::EventBased { .. } => None,
+        }
+    }
+}
+
+impl JobExecutionResult {
+    fn failure(job_id: &str, message: String, execution_time_ms: u64) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            success: false,
+            exit_code: None,
+            execution_time_ms,
+            stdout: String::new(),
+            stderr: message.clone(),
+            error_message: Some(message),
+            memory_usage: None,
+            cpu_usage: None,
         }
     }
 }
@@ -329,15 +469,22 @@ impl AdvancedJobScheduler {
             });
         }
 
+        let state = Arc::new(StateFile {
+            path: config.state_file.clone(),
+            stamp: Mutex::new(None),
+        });
+
         Self {
             config,
             jobs: Arc::new(AsyncRwLock::new(HashMap::new())),
             queue: Arc::new(AsyncRwLock::new(BinaryHeap::new())),
             running_jobs: Arc::new(AsyncRwLock::new(HashMap::new())),
             job_history: Arc::new(AsyncRwLock::new(VecDeque::new())),
+            notifications: Arc::new(Mutex::new(VecDeque::new())),
+            state,
             semaphore,
             shutdown_signal: Arc::new(AtomicBool::new(false)),
-            job_counter: AtomicU64::new(0),
+            job_counter: Arc::new(AtomicU64::new(0)),
             scheduler_handle: None,
             workers,
             rr_counter: AtomicU64::new(0),
@@ -352,6 +499,8 @@ impl AdvancedJobScheduler {
         let job_history = Arc::clone(&self.job_history);
         let semaphore = Arc::clone(&self.semaphore);
         let shutdown_signal = Arc::clone(&self.shutdown_signal);
+        let state = Arc::clone(&self.state);
+        let job_counter = Arc::clone(&self.job_counter);
         let config = self.config.clone();
         let worker_shared = self
             .workers
@@ -367,6 +516,15 @@ impl AdvancedJobScheduler {
             while !shutdown_signal.load(Ordering::Relaxed) {
                 interval.tick().await;
 
+                // 他のプロセスによるジョブ一覧の変更を取り込む
+                if state.changed() {
+                    if let Err(e) =
+                        Self::reload_jobs(&state, &jobs, &queue, &running_jobs, &job_counter).await
+                    {
+                        error!(error = %e, "Error reloading scheduled jobs");
+                    }
+                }
+
                 if let Err(e) = Self::process_scheduled_jobs(
                     &jobs,
                     &queue,
@@ -391,6 +549,8 @@ impl AdvancedJobScheduler {
             let running_jobs = Arc::clone(&self.running_jobs);
             let job_history = Arc::clone(&self.job_history);
             let queue_global = Arc::clone(&self.queue);
+            let notifications = Arc::clone(&self.notifications);
+            let state = Arc::clone(&self.state);
             let semaphore = Arc::clone(&self.semaphore);
             let config = self.config.clone();
             let shutdown = Arc::clone(&self.shutdown_signal);
@@ -432,7 +592,12 @@ impl AdvancedJobScheduler {
                             let running_jobs_clone = Arc::clone(&running_jobs);
                             let job_history_clone = Arc::clone(&job_history);
                             let queue_clone = Arc::clone(&queue_global);
+                            let notifications_clone = Arc::clone(&notifications);
+                            let state_clone = Arc::clone(&state);
                             let config_clone = config.clone();
+                            // Hold the lock until the job is registered so that a job
+                            // finishing right away cannot remove itself first
+                            let mut running = running_jobs.write().await;
                             let handle = tokio::spawn(async move {
                                 let _permit = permit;
                                 Self::execute_job(
@@ -443,6 +608,8 @@ impl AdvancedJobScheduler {
                                     &running_jobs_clone,
                                     &job_history_clone,
                                     &queue_clone,
+                                    &notifications_clone,
+                                    &state_clone,
                                     &config_clone,
                                 )
                                 .await
@@ -453,10 +620,7 @@ impl AdvancedJobScheduler {
                                 handle,
                                 pid: None,
                             };
-                            running_jobs
-                                .write()
-                                .await
-                                .insert(queued_job.job_id.clone(), running_job);
+                            running.insert(queued_job.job_id.clone(), running_job);
                             continue;
                         } else if let Ok(mut q) = wqueue.lock() {
                             q.push_back(queued_job);
@@ -486,6 +650,37 @@ impl AdvancedJobScheduler {
         }
     }
 
+    /// 既定値で埋めたジョブを作る（IDは `{prefix}_{連番}`）
+    ///
+    /// 作業ディレクトリと環境変数は現在のプロセスのものになります。
+    pub fn new_job(&self, prefix: &str, command: String, schedule: JobSchedule) -> ScheduledJob {
+        let job_id = format!(
+            "{prefix}_{}",
+            self.job_counter.fetch_add(1, Ordering::Relaxed)
+        );
+        ScheduledJob {
+            id: job_id,
+            name: command.clone(),
+            command,
+            args: Vec::new(),
+            working_dir: std::env::current_dir()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            environment: std::env::vars().collect(),
+            schedule,
+            priority: 5,
+            timeout_secs: self.config.default_timeout_secs,
+            retry_config: RetryConfig::default(),
+            dependencies: Vec::new(),
+            notifications: NotificationConfig::default(),
+            created_at: SystemTime::now(),
+            enabled: true,
+            metadata: HashMap::new(),
+            nice: 0,
+        }
+    }
+
     /// ジョブをスケジュール
     pub async fn schedule_job(&self, mut job: ScheduledJob) -> Result<String> {
         let job_id = job.id.clone();
@@ -511,6 +706,7 @@ impl AdvancedJobScheduler {
             let mut jobs = self.jobs.write().await;
             jobs.insert(job_id.clone(), job);
         }
+        self.state.save(&self.jobs).await?;
 
         info!(
             job_id = %job_id,
@@ -523,96 +719,32 @@ impl AdvancedJobScheduler {
 
     /// at風のジョブをスケジュール
     pub async fn schedule_at(&self, command: String, run_at: SystemTime) -> Result<String> {
-        let job_id = format!("at_{}", self.job_counter.fetch_add(1, Ordering::Relaxed));
-
-        let job = ScheduledJob {
-            id: job_id.clone(),
-            name: format!("At job: {command}"),
-            command: command.clone(),
-            args: Vec::new(),
-            working_dir: std::env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-            environment: std::env::vars().collect(),
-            schedule: JobSchedule::Once { run_at },
-            priority: 5,
-            timeout_secs: self.config.default_timeout_secs,
-            retry_config: RetryConfig::default(),
-            dependencies: Vec::new(),
-            notifications: NotificationConfig::default(),
-            created_at: SystemTime::now(),
-            enabled: true,
-            metadata: HashMap::new(),
-            nice: 0,
-        };
-
+        let mut job = self.new_job("at", command, JobSchedule::Once { run_at });
+        job.name = format!("At job: {}", job.command);
         self.schedule_job(job).await
     }
 
     /// cron風のジョブをスケジュール
     pub async fn schedule_cron(&self, command: String, cron_expression: String) -> Result<String> {
-        let job_id = format!("cron_{}", self.job_counter.fetch_add(1, Ordering::Relaxed));
-
         let next_run = self.parse_cron_expression(&cron_expression).await?;
-
-        let job = ScheduledJob {
-            id: job_id.clone(),
-            name: format!("Cron job: {command}"),
-            command: command.clone(),
-            args: Vec::new(),
-            working_dir: std::env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-            environment: std::env::vars().collect(),
-            schedule: JobSchedule::Recurring {
-                cron_expression,
-                next_run,
-                last_run: None,
-            },
-            priority: 5,
-            timeout_secs: self.config.default_timeout_secs,
-            retry_config: RetryConfig::default(),
-            dependencies: Vec::new(),
-            notifications: NotificationConfig::default(),
-            created_at: SystemTime::now(),
-            enabled: true,
-            metadata: HashMap::new(),
-            nice: 0,
+        let schedule = JobSchedule::Recurring {
+            cron_expression,
+            next_run,
+            last_run: None,
         };
-
+        let mut job = self.new_job("cron", command, schedule);
+        job.name = format!("Cron job: {}", job.command);
         self.schedule_job(job).await
     }
 
     /// 間隔実行ジョブをスケジュール（秒間隔）
     pub async fn schedule_interval(&self, command: String, interval_secs: u64) -> Result<String> {
-        let job_id = format!("int_{}", self.job_counter.fetch_add(1, Ordering::Relaxed));
-        let nr = SystemTime::now() + Duration::from_secs(interval_secs.max(1));
-        let job = ScheduledJob {
-            id: job_id.clone(),
-            name: format!("Interval job: {command}"),
-            command: command.clone(),
-            args: Vec::new(),
-            working_dir: std::env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-            environment: std::env::vars().collect(),
-            schedule: JobSchedule::Interval {
-                interval_secs,
-                next_run: nr,
-            },
-            priority: 5,
-            timeout_secs: self.config.default_timeout_secs,
-            retry_config: RetryConfig::default(),
-            dependencies: Vec::new(),
-            notifications: NotificationConfig::default(),
-            created_at: SystemTime::now(),
-            enabled: true,
-            metadata: HashMap::new(),
-            nice: 0,
+        let schedule = JobSchedule::Interval {
+            interval_secs,
+            next_run: SystemTime::now() + Duration::from_secs(interval_secs.max(1)),
         };
+        let mut job = self.new_job("int", command, schedule);
+        job.name = format!("Interval job: {}", job.command);
         self.schedule_job(job).await
     }
 
@@ -636,13 +768,16 @@ impl AdvancedJobScheduler {
         };
         // Compute next run outside the lock
         let next_run = self.calculate_next_run(&schedule_clone).await?;
-        let mut q = self.queue.write().await;
-        q.push(Reverse(QueuedJob {
-            job_id: job_id.to_string(),
-            scheduled_time: next_run,
-            priority,
-            attempt: 0,
-        }));
+        {
+            let mut q = self.queue.write().await;
+            q.push(Reverse(QueuedJob {
+                job_id: job_id.to_string(),
+                scheduled_time: next_run,
+                priority,
+                attempt: 0,
+            }));
+        }
+        self.state.save(&self.jobs).await?;
         Ok(true)
     }
 
@@ -653,14 +788,17 @@ impl AdvancedJobScheduler {
             job.enabled = false;
             drop(jobs_w);
             // Remove queued entries
-            let mut queue = self.queue.write().await;
-            let mut new_queue = BinaryHeap::new();
-            while let Some(Reverse(job)) = queue.pop() {
-                if job.job_id != job_id {
-                    new_queue.push(Reverse(job));
+            {
+                let mut queue = self.queue.write().await;
+                let mut new_queue = BinaryHeap::new();
+                while let Some(Reverse(job)) = queue.pop() {
+                    if job.job_id != job_id {
+                        new_queue.push(Reverse(job));
+                    }
                 }
+                *queue = new_queue;
             }
-            *queue = new_queue;
+            self.state.save(&self.jobs).await?;
             return Ok(true);
         }
         Ok(false)
@@ -695,11 +833,38 @@ impl AdvancedJobScheduler {
                 running_job.handle.abort();
             }
         }
+        self.state.save(&self.jobs).await?;
 
         info!(job_id = %job_id, "Job cancelled successfully");
         Ok(true)
     }
 
+    /// 保存先のジョブ一覧を読み込む（前回読み書きした後に変更があった場合のみ）
+    ///
+    /// 実行中のジョブは終了後に自身で再スケジュールするため、キューには入れません。
+    /// 戻り値は読み込み後のジョブ数です。
+    pub async fn reload(&self) -> Result<usize> {
+        if self.state.changed() {
+            Self::reload_jobs(
+                &self.state,
+                &self.jobs,
+                &self.queue,
+                &self.running_jobs,
+                &self.job_counter,
+            )
+            .await?;
+        }
+        Ok(self.jobs.read().await.len())
+    }
+
+    /// 通知待ちの実行結果を取り出す
+    pub fn take_notifications(&self) -> Vec<JobNotification> {
+        self.notifications
+            .lock()
+            .map(|mut pending| pending.drain(..).collect())
+            .unwrap_or_default()
+    }
+
     /// ジョブリストを取得
     pub async fn list_jobs(&self) -> Vec<ScheduledJob> {
         let jobs = self.jobs.read().await;
@@ -829,6 +994,47 @@ impl AdvancedJobScheduler {
         Ok(())
     }
 
+    /// 保存先のジョブ一覧でメモリ上のジョブとキューを置き換える
+    async fn reload_jobs(
+        state: &StateFile,
+        jobs: &Arc<AsyncRwLock<HashMap<String, ScheduledJob>>>,
+        queue: &Arc<AsyncRwLock<BinaryHeap<Reverse<QueuedJob>>>>,
+        running_jobs: &Arc<AsyncRwLock<HashMap<String, RunningJob>>>,
+        job_counter: &AtomicU64,
+    ) -> Result<()> {
+        let Some(loaded) = state.load()? else {
+            return Ok(());
+        };
+        let running: HashSet<String> = running_jobs.read().await.keys().cloned().collect();
+        let mut reloaded = HashMap::new();
+        let mut queued = BinaryHeap::new();
+        for job in loaded {
+            // 以降に作るジョブのIDが重ならないように連番を進める
+            if let Some(n) = job
+                .id
+                .rsplit_once('_')
+                .and_then(|(_, n)| n.parse::<u64>().ok())
+            {
+                job_counter.fetch_max(n + 1, Ordering::Relaxed);
+            }
+            if job.enabled && !running.contains(&job.id) {
+                if let Some(next_run) = job.schedule.next_run() {
+                    queued.push(Reverse(QueuedJob {
+                        job_id: job.id.clone(),
+                        scheduled_time: next_run,
+                        priority: job.priority,
+                        attempt: 0,
+                    }));
+                }
+            }
+            reloaded.insert(job.id.clone(), job);
+        }
+        info!(jobs = reloaded.len(), "Scheduled jobs reloaded");
+        *jobs.write().await = reloaded;
+        *queue.write().await = queued;
+        Ok(())
+    }
+
     /// ジョブを実行
     #[allow(clippy::too_many_arguments)] // Context struct planned; keeping signature stable for call sites/tests
     async fn execute_job(
@@ -839,6 +1045,8 @@ impl AdvancedJobScheduler {
         running_jobs: &Arc<AsyncRwLock<HashMap<String, RunningJob>>>,
         job_history: &Arc<AsyncRwLock<VecDeque<JobHistoryEntry>>>,
         queue: &Arc<AsyncRwLock<BinaryHeap<Reverse<QueuedJob>>>>,
+        notifications: &Arc<Mutex<VecDeque<JobNotification>>>,
+        state: &Arc<StateFile>,
         config: &SchedulerConfig,
     ) -> JobExecutionResult {
         let start_time = Instant::now();
@@ -849,9 +1057,10 @@ impl AdvancedJobScheduler {
             jobs_guard.get(job_id).cloned()
         };
 
-        let result = if let Some(job) = job {
+        let result = if let Some(job) = &job {
             // Skip disabled jobs gracefully (do not reschedule)
             if !job.enabled {
+                running_jobs.write().await.remove(job_id);
                 return JobExecutionResult {
                     job_id: job_id.to_string(),
                     success: true,
@@ -869,35 +1078,13 @@ impl AdvancedJobScheduler {
                 command = %job.command,
                 "Starting job execution"
             );
-
-            // 実際のコマンド実行（簡略化）
-            let mut result = JobExecutionResult {
-                job_id: job_id.to_string(),
-                success: true,
-                exit_code: Some(0),
-                execution_time_ms: 0,
-                stdout: "Job executed successfully".to_string(),
-                stderr: String::new(),
-                error_message: None,
-                memory_usage: Some(1024 * 1024), // 1MB
-                cpu_usage: Some(5.0),
-            };
-
-            // 実行時間を記録
-            result.execution_time_ms = start_time.elapsed().as_millis() as u64;
-            result
+            Self::run_command(job, config).await
         } else {
-            JobExecutionResult {
-                job_id: job_id.to_string(),
-                success: false,
-                exit_code: Some(1),
-                execution_time_ms: start_time.elapsed().as_millis() as u64,
-                stdout: String::new(),
-                stderr: "Job not found".to_string(),
-                error_message: Some("Job not found".to_string()),
-                memory_usage: None,
-                cpu_usage: None,
-            }
+            JobExecutionResult::failure(
+                job_id,
+                "Job not found".to_string(),
+                start_time.elapsed().as_millis() as u64,
+            )
         };
 
         // 実行中ジョブから削除
@@ -927,14 +1114,32 @@ impl AdvancedJobScheduler {
             }
         }
 
-        // 次回実行時刻の計算と再スケジュール処理
-        if let Some(mut job) = {
+        // 次回実行時刻の計算と再スケジュール処理（実行中に取り消されたジョブは除く）
+        let current = {
             let guard = jobs.read().await;
             guard.get(job_id).cloned()
-        } {
-            // 成否に応じて再スケジュール方針
-            let mut maybe_next: Option<SystemTime> = None;
-            if result.success {
+        };
+        if let Some(mut job) = current {
+            let retry = !result.success && attempt < job.retry_config.max_retries;
+            // (実行時刻, 試行回数)
+            let mut maybe_next: Option<(SystemTime, u32)> = None;
+            if retry {
+                let mut delay = Duration::from_secs(job.retry_config.retry_interval_secs);
+                if job.retry_config.exponential_backoff {
+                    // Exponential backoff by doubling per attempt, clamped to max_delay_secs
+                    let mut factor: u32 = 1;
+                    for _ in 0..attempt.min(16) {
+                        factor = factor.saturating_mul(2);
+                    }
+                    delay = delay.saturating_mul(factor);
+                    let max = Duration::from_secs(job.retry_config.max_delay_secs);
+                    if delay > max {
+                        delay = max;
+                    }
+                }
+                maybe_next = Some((SystemTime::now() + delay, attempt.saturating_add(1)));
+            } else {
+                // 成否にかかわらず通常のスケジュールに戻る
                 match &mut job.schedule {
                     JobSchedule::Once { .. } => {
                         // 完了後はジョブを削除
@@ -946,11 +1151,11 @@ impl AdvancedJobScheduler {
                         next_run,
                         last_run,
                     } => {
-                        *last_run = Some(SystemTime::now());
-                        // 簡易: 既存のパーサで次回を算出
-                        if let Ok(nr) = Self::parse_cron_expression_static(cron_expression).await {
+                        let now = SystemTime::now();
+                        *last_run = Some(now);
+                        if let Some(nr) = next_cron_run(cron_expression, now) {
                             *next_run = nr;
-                            maybe_next = Some(nr);
+                            maybe_next = Some((nr, 0));
                         }
                     }
                     JobSchedule::Interval {
@@ -960,30 +1165,12 @@ impl AdvancedJobScheduler {
                         let base = SystemTime::now();
                         let nr = base + Duration::from_secs(*interval_secs);
                         *next_run = nr;
-                        maybe_next = Some(nr);
+                        maybe_next = Some((nr, 0));
                     }
                     JobSchedule::EventBased { .. } => { /* wait for event */ }
                 }
-            } else {
-                // 失敗時の再試行
-                if attempt < job.retry_config.max_retries {
-                    let mut delay = Duration::from_secs(job.retry_config.retry_interval_secs);
-                    if job.retry_config.exponential_backoff {
-                        // Exponential backoff by doubling per attempt, clamped to max_delay_secs
-                        let mut factor: u32 = 1;
-                        for _ in 0..attempt.min(16) {
-                            factor = factor.saturating_mul(2);
-                        }
-                        delay = delay.saturating_mul(factor);
-                        let max = Duration::from_secs(job.retry_config.max_delay_secs);
-                        if delay > max {
-                            delay = max;
-                        }
-                    }
-                    maybe_next = Some(SystemTime::now() + delay);
-                }
             }
-            if let Some(nr) = maybe_next {
+            if let Some((nr, next_attempt)) = maybe_next {
                 // 更新を確定しキューへ投入
                 let priority_for_requeue = job.priority;
                 {
@@ -997,9 +1184,32 @@ impl AdvancedJobScheduler {
                     job_id: job_id.to_string(),
                     scheduled_time: nr,
                     priority: priority_for_requeue,
-                    attempt: attempt.saturating_add(1),
+                    attempt: next_attempt,
                 }));
             }
+            if let Err(e) = state.save(jobs).await {
+                error!(job_id = %job_id, error = %e, "Error saving scheduled jobs");
+            }
+
+            // 再試行しない結果だけを通知する
+            let wanted = if result.success {
+                job.notifications.on_success
+            } else {
+                job.notifications.on_failure
+            };
+            if !retry && wanted {
+                if let Ok(mut pending) = notifications.lock() {
+                    pending.push_back(JobNotification {
+                        job_id: job_id.to_string(),
+                        name: job.name.clone(),
+                        command: job.command.clone(),
+                        channels: job.notifications.channels.clone(),
+                        custom_message: job.notifications.custom_message.clone(),
+                        finished_at: SystemTime::now(),
+                        result: result.clone(),
+                    });
+                }
+            }
         }
 
         info!(
@@ -1012,134 +1222,230 @@ impl AdvancedJobScheduler {
         result
     }
 
-    /// 次回実行時刻を計算
-    async fn calculate_next_run(&self, schedule: &JobSchedule) -> Result<SystemTime> {
-        match schedule {
-            JobSchedule::Once { run_at } => Ok(*run_at),
-            JobSchedule::Recurring { next_run, .. } => Ok(*next_run),
-            JobSchedule::Interval { next_run, .. } => Ok(*next_run),
-            JobSchedule::EventBased { .. } => {
-                // イベントベースの場合は遠い未来を返す
-                Ok(SystemTime::now() + Duration::from_secs(86400 * 365))
+    /// ジョブのコマンド行を設定されたシェルで実行し、出力を集める
+    async fn run_command(job: &ScheduledJob, config: &SchedulerConfig) -> JobExecutionResult {
+        let start_time = Instant::now();
+        let elapsed = || start_time.elapsed().as_millis() as u64;
+        let Some((program, leading)) = config.shell.split_first() else {
+            return JobExecutionResult::failure(&job.id, "no shell configured".into(), 0);
+        };
+
+        let mut line = job.command.clone();
+        for arg in &job.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        let mut command = tokio::process::Command::new(program);
+        command
+            .args(leading)
+            .arg(&line)
+            .envs(&job.environment)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        if !job.working_dir.is_empty() {
+            command.current_dir(&job.working_dir);
+        }
+
+        // タイムアウト0は無制限
+        let output = if job.timeout_secs == 0 {
+            command.output().await
+        } else {
+            let limit = Duration::from_secs(job.timeout_secs);
+            match tokio::time::timeout(limit, command.output()).await {
+                Ok(output) => output,
+                Err(_) => {
+                    let message = format!("timed out after {}s", job.timeout_secs);
+                    return JobExecutionResult::failure(&job.id, message, elapsed());
+                }
             }
+        };
+        match output {
+            Ok(output) => JobExecutionResult {
+                job_id: job.id.clone(),
+                success: output.status.success(),
+                exit_code: output.status.code(),
+                execution_time_ms: elapsed(),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                error_message: None,
+                memory_usage: None,
+                cpu_usage: None,
+            },
+            Err(e) => JobExecutionResult::failure(&job.id, format!("{program}: {e}"), elapsed()),
         }
     }
 
+    /// 次回実行時刻を計算
+    async fn calculate_next_run(&self, schedule: &JobSchedule) -> Result<SystemTime> {
+        // イベントベースの場合は遠い未来を返す
+        Ok(schedule
+            .next_run()
+            .unwrap_or_else(|| SystemTime::now() + Duration::from_secs(86400 * 365)))
+    }
+
     /// cron式を解析
     async fn parse_cron_expression(&self, cron_expression: &str) -> Result<SystemTime> {
-        Self::parse_cron_expression_static(cron_expression).await
+        let spec = CronSpec::parse(cron_expression)
+            .map_err(|e| anyhow(format!("invalid cron expression '{cron_expression}': {e}")))?;
+        spec.next_after(SystemTime::now()).ok_or_else(|| {
+            anyhow(format!(
+                "cron expression '{cron_expression}' never matches a date"
+            ))
+        })
     }
+}
 
-    /// Static helper that computes next run time from a cron expression.
-    ///
-    /// Full 5-field cron subset with lists, ranges and steps:
-    /// minute hour day month weekday
-    /// - Each field supports: '*' | '*/N' | 'A-B' | 'A-B/N' | 'A,B,C' | single number
-    /// - Weekday: 0-6 (0=Sun)
-    ///   Strategy: iterate minute-by-minute up to a reasonable horizon (e.g., 1 year) and pick first match.
-    async fn parse_cron_expression_static(cron_expression: &str) -> Result<SystemTime> {
-        use chrono::{Datelike, Timelike};
-        let parts: Vec<&str> = cron_expression.split_whitespace().collect();
-        if parts.len() < 5 {
-            return Ok(SystemTime::now() + Duration::from_secs(3600));
+/// cron式を検証する
+///
+/// 5つのフィールド（分 時 日 月 曜日）と `@daily` などの別名を受け付けます。
+/// 各フィールドは `*`、`*/N`、`A`、`A-B`、`A-B/N`、`A/N` とそのカンマ区切りで、
+/// 月と曜日は `jan`、`mon` のような英語の略称も使えます。曜日の7は日曜日です。
+pub fn validate_cron_expression(expression: &str) -> std::result::Result<(), String> {
+    CronSpec::parse(expression).map(|_| ())
+}
+
+/// `after` より後で `expression` に一致する最初の時刻（ローカル時刻で評価）
+///
+/// 式が不正な場合や、8年以内に一致する日がない場合は `None` を返します。
+pub fn next_cron_run(expression: &str, after: SystemTime) -> Option<SystemTime> {
+    CronSpec::parse(expression).ok()?.next_after(after)
+}
+
+/// 解析済みのcron式（各フィールドは許可する値のビット集合）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CronSpec {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日と曜日の両方が `*` 以外なら、どちらかに一致すればよい
+    any_day: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl CronSpec {
+    fn parse(expression: &str) -> std::result::Result<Self, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other if other.starts_with('@') => return Err(format!("unknown alias {other}")),
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+        let mut weekdays = Self::field(weekday, "weekday", 0, 7, &WEEKDAY_NAMES)?;
+        // 7は日曜日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
         }
-        let (min_s, hour_s, day_s, month_s, wday_s) =
-            (parts[0], parts[1], parts[2], parts[3], parts[4]);
-
-        // Parser for a field into a matcher closure
-        fn parse_set(spec: &str, min: u32, max: u32) -> Box<dyn Fn(u32) -> bool + Send + Sync> {
-            // '*' catch-all
-            if spec == "*" {
-                return Box::new(|_| true);
+        Ok(Self {
+            minutes: Self::field(minute, "minute", 0, 59, &[])?,
+            hours: Self::field(hour, "hour", 0, 23, &[])?,
+            days: Self::field(day, "day", 1, 31, &[])?,
+            months: Self::field(month, "month", 1, 12, &MONTH_NAMES)?,
+            weekdays,
+            any_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+
+    /// 1フィールドを許可する値のビット集合にする
+    fn field(
+        spec: &str,
+        what: &str,
+        min: u32,
+        max: u32,
+        names: &[&str],
+    ) -> std::result::Result<u64, String> {
+        let value = |text: &str| -> std::result::Result<u32, String> {
+            let lower = text.to_ascii_lowercase();
+            let named = names
+                .iter()
+                .position(|name| *name == lower)
+                .map(|i| i as u32 + min);
+            match named.or_else(|| text.parse().ok()) {
+                Some(v) if (min..=max).contains(&v) => Ok(v),
+                _ => Err(format!("{what} '{text}' is not in {min}-{max}")),
             }
-            // '*/N'
-            if let Some(step_str) = spec.strip_prefix("*/") {
-                if let Ok(step) = step_str.parse::<u32>() {
-                    if step >= 1 {
-                        return Box::new(move |v| v % step == 0);
-                    }
-                }
+        };
+        let mut set = 0u64;
+        for entry in spec.split(',') {
+            let (range, step) = match entry.split_once('/') {
+                Some((range, step)) => match step.parse::<u32>() {
+                    Ok(step) if step > 0 => (range, step),
+                    _ => return Err(format!("bad step in {what} '{entry}'")),
+                },
+                None => (entry, 1),
+            };
+            let (low, high) = if range == "*" {
+                (min, max)
+            } else if let Some((a, b)) = range.split_once('-') {
+                (value(a)?, value(b)?)
+            } else if entry.contains('/') {
+                (value(range)?, max)
+            } else {
+                let v = value(range)?;
+                (v, v)
+            };
+            if low > high {
+                return Err(format!("{what} range '{range}' is backwards"));
             }
-            // lists with possible ranges and steps: A,B,C  A-B  A-B/N
-            let entries: Vec<&str> = spec.split(',').collect();
-            let mut ranges: Vec<(u32, u32, u32)> = Vec::new(); // (start,end,step)
-            for e in entries {
-                if let Some((lhs, rhs)) = e.split_once('/') {
-                    let step = rhs.parse::<u32>().unwrap_or(1).max(1);
-                    if let Some((a_str, b_str)) = lhs.split_once('-') {
-                        if let (Ok(mut a), Ok(mut b)) = (a_str.parse::<u32>(), b_str.parse::<u32>())
-                        {
-                            if a > b {
-                                std::mem::swap(&mut a, &mut b);
-                            }
-                            a = a.clamp(min, max);
-                            b = b.clamp(min, max);
-                            ranges.push((a, b, step));
+            for v in (low..=high).step_by(step as usize) {
+                set |= 1 << v;
+            }
+        }
+        Ok(set)
+    }
+
+    fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let by_day = self.days & (1 << day) != 0;
+        let by_weekday = self.weekdays & (1 << weekday) != 0;
+        if self.any_day {
+            by_day || by_weekday
+        } else {
+            by_day && by_weekday
+        }
+    }
+
+    /// `after` より後の最初の一致時刻
+    fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        use chrono::{Datelike, Local, TimeZone, Timelike};
+        let after: chrono::DateTime<Local> = after.into();
+        let start =
+            after.naive_local().with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let mut date = start.date();
+        // 2月29日だけの指定でも見つかるよう8年分探す
+        for _ in 0..(366 * 8) {
+            if self.months & (1 << date.month()) != 0
+                && self.day_matches(date.day(), date.weekday().num_days_from_sunday())
+            {
+                for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                    for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                        let Some(candidate) = date.and_hms_opt(hour, minute, 0) else {
+                            continue;
+                        };
+                        if candidate < start {
                             continue;
                         }
-                    } else if let Ok(v) = lhs.parse::<u32>() {
-                        let v = v.clamp(min, max);
-                        ranges.push((v, v, step));
-                        continue;
-                    }
-                }
-                if let Some((a_str, b_str)) = e.split_once('-') {
-                    if let (Ok(mut a), Ok(mut b)) = (a_str.parse::<u32>(), b_str.parse::<u32>()) {
-                        if a > b {
-                            std::mem::swap(&mut a, &mut b);
+                        // 夏時間の切り替えで存在しない時刻は飛ばす
+                        if let Some(at) = Local.from_local_datetime(&candidate).earliest() {
+                            return Some(at.into());
                         }
-                        a = a.clamp(min, max);
-                        b = b.clamp(min, max);
-                        ranges.push((a, b, 1));
-                        continue;
                     }
                 }
-                if let Ok(v) = e.parse::<u32>() {
-                    let v = v.clamp(min, max);
-                    ranges.push((v, v, 1));
-                }
-            }
-            if ranges.is_empty() {
-                return Box::new(|_| false);
             }
-            Box::new(move |v| {
-                for (a, b, step) in &ranges {
-                    if v < *a || v > *b {
-                        continue;
-                    }
-                    if ((v - *a) % *step) == 0 {
-                        return true;
-                    }
-                }
-                false
-            })
+            date = date.succ_opt()?;
         }
-
-        let match_min = parse_set(min_s, 0, 59);
-        let match_hour = parse_set(hour_s, 0, 23);
-        let match_day = parse_set(day_s, 1, 31);
-        let match_month = parse_set(month_s, 1, 12);
-        let match_wday = parse_set(wday_s, 0, 6);
-
-        let mut t = chrono::Utc::now()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
-            + chrono::Duration::minutes(1);
-        // Search horizon: one year
-        for _ in 0..(60 * 24 * 366) {
-            let m = t.minute();
-            let h = t.hour();
-            let d = t.day();
-            let mo = t.month();
-            let wd = t.weekday().num_days_from_sunday();
-            if match_min(m) && match_hour(h) && match_day(d) && match_month(mo) && match_wday(wd) {
-                return Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(t.timestamp() as u64));
-            }
-            t += chrono::Duration::minutes(1);
-        }
-        Ok(SystemTime::now() + Duration::from_secs(3600))
+        None
     }
 }
 
@@ -1198,4 +1504,92 @@ mod tests {
         let job_id = result.unwrap();
         assert!(job_id.starts_with("at_"));
     }
+
+    #[test]
+    fn test_cron_validation() {
+        assert!(validate_cron_expression("*/15 9-17 * * mon-fri").is_ok());
+        assert!(validate_cron_expression("0 0 1,15 jan,jul 7").is_ok());
+        assert!(validate_cron_expression("@daily").is_ok());
+        assert!(validate_cron_expression("* * * *").is_err());
+        assert!(validate_cron_expression("60 * * * *").is_err());
+        assert!(validate_cron_expression("5-1 * * * *").is_err());
+        assert!(validate_cron_expression("*/0 * * * *").is_err());
+        assert!(validate_cron_expression("@sometimes").is_err());
+    }
+
+    #[test]
+    fn test_next_cron_run() {
+        use chrono::{Local, TimeZone, Timelike};
+        let after: SystemTime = Local
+            .with_ymd_and_hms(2030, 3, 4, 10, 20, 30)
+            .unwrap()
+            .into();
+
+        let next: chrono::DateTime<Local> = next_cron_run("30 4 * * *", after).unwrap().into();
+        assert_eq!(
+            (next.format("%F").to_string(), next.hour(), next.minute()),
+            ("2030-03-05".to_string(), 4, 30)
+        );
+
+        // 2030-03-04 は月曜日なので、日曜日の指定は次の日曜日になる
+        let next: chrono::DateTime<Local> = next_cron_run("0 12 * * 7", after).unwrap().into();
+        assert_eq!(next.format("%F %H:%M").to_string(), "2030-03-10 12:00");
+
+        // 日と曜日の両方を指定するとどちらかに一致すればよい
+        let next: chrono::DateTime<Local> = next_cron_run("0 0 31 * tue", after).unwrap().into();
+        assert_eq!(next.format("%F").to_string(), "2030-03-05");
+
+        assert!(next_cron_run("0 0 30 2 *", after).is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_jobs_run_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("jobs.json");
+        let config = SchedulerConfig {
+            check_interval_secs: 1,
+            num_workers: 1,
+            state_file: Some(state_file.clone()),
+            ..SchedulerConfig::default()
+        };
+        let mut scheduler = AdvancedJobScheduler::new(config.clone());
+        scheduler.start().await.unwrap();
+
+        let mut job = scheduler.new_job(
+            "at",
+            "echo done; exit 3".to_string(),
+            JobSchedule::Once {
+                run_at: SystemTime::now(),
+            },
+        );
+        job.retry_config.max_retries = 0;
+        job.notifications.on_success = true;
+        scheduler.schedule_job(job).await.unwrap();
+        let later = scheduler
+            .schedule_cron("true".to_string(), "0 0 1 1 *".to_string())
+            .await
+            .unwrap();
+
+        let mut notifications = Vec::new();
+        for _ in 0..100 {
+            notifications.extend(scheduler.take_notifications());
+            if !notifications.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        scheduler.stop().await;
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].result.exit_code, Some(3));
+        assert_eq!(notifications[0].result.stdout, "done\n");
+
+        // 実行済みの単発ジョブは保存先からも消えている
+        let restored = AdvancedJobScheduler::new(config);
+        assert_eq!(restored.reload().await.unwrap(), 1);
+        let jobs = restored.list_jobs().await;
+        assert_eq!(jobs[0].id, later);
+        let next = restored.new_job("cron", "true".to_string(), jobs[0].schedule.clone());
+        assert_ne!(next.id, later);
+    }
 }
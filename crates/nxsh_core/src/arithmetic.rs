@@ -0,0 +1,439 @@
+//! Shell arithmetic evaluation
+//!
+//! Implements the integer expression language used by `((...))`, `$((...))`
+//! and the clauses of C-style `for ((init; cond; update))` loops. Semantics
+//! follow bash: 64-bit signed integers, C operator precedence, assignment
+//! operators that write back into shell variables, and short-circuiting
+//! `&&`, `||` and `?:` that suppress side effects in the skipped branch.
+
+use crate::context::ShellContext;
+use crate::error::{ErrorKind, RuntimeErrorKind, ShellError, ShellResult};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/// Operators ordered longest-first so the tokenizer matches greedily
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>",
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "|",
+    "^", "?", ":", ",",
+];
+
+fn arith_error(message: impl Into<String>) -> ShellError {
+    ShellError::new(
+        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
+        message.into(),
+    )
+}
+
+fn parse_number(text: &str) -> ShellResult<i64> {
+    let parsed = if let Some((base, digits)) = text.split_once('#') {
+        let base: u32 = base
+            .parse()
+            .map_err(|_| arith_error(format!("invalid arithmetic base: {text}")))?;
+        if !(2..=36).contains(&base) {
+            return Err(arith_error(format!("invalid arithmetic base: {text}")));
+        }
+        i64::from_str_radix(digits, base)
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8)
+    } else {
+        text.parse::<i64>()
+    };
+    parsed.map_err(|_| arith_error(format!("value too great for base: {text}")))
+}
+
+fn tokenize(expr: &str) -> ShellResult<Vec<Token>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    'outer: while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '#') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(parse_number(&text)?));
+            continue;
+        }
+        if c == '$' || c.is_ascii_alphabetic() || c == '_' {
+            // `$name` and `${name}` are accepted as plain variable references
+            let mut j = i;
+            if c == '$' {
+                j += 1;
+            }
+            let braced = j < chars.len() && chars[j] == '{';
+            if braced {
+                j += 1;
+            }
+            let start = j;
+            while j < chars.len() && (chars[j].is_ascii_alphanumeric() || chars[j] == '_') {
+                j += 1;
+            }
+            if start == j {
+                return Err(arith_error(format!("syntax error in expression: {expr}")));
+            }
+            let name: String = chars[start..j].iter().collect();
+            if braced {
+                if j >= chars.len() || chars[j] != '}' {
+                    return Err(arith_error(format!("bad substitution: {expr}")));
+                }
+                j += 1;
+            }
+            tokens.push(Token::Ident(name));
+            i = j;
+            continue;
+        }
+        if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+            continue;
+        }
+        if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+            continue;
+        }
+        for &op in OPERATORS {
+            let len = op.len();
+            if i + len <= chars.len() && chars[i..i + len].iter().copied().eq(op.chars()) {
+                tokens.push(Token::Op(op));
+                i += len;
+                continue 'outer;
+            }
+        }
+        return Err(arith_error(format!(
+            "syntax error: invalid arithmetic operator (error token is \"{}\")",
+            chars[i..].iter().collect::<String>()
+        )));
+    }
+    Ok(tokens)
+}
+
+struct Evaluator<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    ctx: &'a ShellContext,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect_op(&mut self, op: &str) -> ShellResult<()> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            other => Err(arith_error(format!(
+                "syntax error: expected `{op}`, found {other:?}"
+            ))),
+        }
+    }
+
+    fn read_var(&self, name: &str) -> ShellResult<i64> {
+        let raw = self.ctx.get_var(name).unwrap_or_default();
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return Ok(0);
+        }
+        if let Ok(n) = parse_number(raw) {
+            return Ok(n);
+        }
+        // bash re-evaluates variable contents as an expression
+        if self.depth >= 32 {
+            return Err(arith_error(format!(
+                "expression recursion level exceeded: {name}"
+            )));
+        }
+        evaluate_with_depth(raw, self.ctx, self.depth + 1)
+    }
+
+    fn write_var(&self, name: &str, value: i64, live: bool) {
+        if live {
+            self.ctx.set_var(name.to_string(), value.to_string());
+        }
+    }
+
+    /// comma := assign (',' assign)*
+    fn comma(&mut self, live: bool) -> ShellResult<i64> {
+        let mut value = self.assign(live)?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            value = self.assign(live)?;
+        }
+        Ok(value)
+    }
+
+    /// assign := IDENT assign_op assign | ternary
+    fn assign(&mut self, live: bool) -> ShellResult<i64> {
+        if let (Some(Token::Ident(name)), Some(&Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            let is_assign = matches!(
+                op,
+                "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "<<=" | ">>=" | "&=" | "|=" | "^="
+            );
+            if is_assign {
+                let name = name.clone();
+                self.pos += 2;
+                let rhs = self.assign(live)?;
+                let value = if op == "=" {
+                    rhs
+                } else {
+                    let current = self.read_var(&name)?;
+                    apply_binary(&op[..op.len() - 1], current, rhs, live)?
+                };
+                self.write_var(&name, value, live);
+                return Ok(value);
+            }
+        }
+        self.ternary(live)
+    }
+
+    /// ternary := binary(1) ('?' comma ':' ternary)?
+    fn ternary(&mut self, live: bool) -> ShellResult<i64> {
+        let cond = self.binary(0, live)?;
+        if self.peek_op() == Some("?") {
+            self.pos += 1;
+            let then_value = self.comma(live && cond != 0)?;
+            self.expect_op(":")?;
+            let else_value = self.ternary(live && cond == 0)?;
+            return Ok(if cond != 0 { then_value } else { else_value });
+        }
+        Ok(cond)
+    }
+
+    /// Precedence climbing over the binary operator table
+    fn binary(&mut self, min_level: usize, live: bool) -> ShellResult<i64> {
+        let mut lhs = self.unary(live)?;
+        while let Some(op) = self.peek_op() {
+            let Some(level) = binary_level(op) else { break };
+            if level < min_level {
+                break;
+            }
+            self.pos += 1;
+            match op {
+                "&&" => {
+                    let rhs = self.binary(level + 1, live && lhs != 0)?;
+                    lhs = i64::from(lhs != 0 && rhs != 0);
+                }
+                "||" => {
+                    let rhs = self.binary(level + 1, live && lhs == 0)?;
+                    lhs = i64::from(lhs != 0 || rhs != 0);
+                }
+                "**" => {
+                    // Right associative
+                    let rhs = self.binary(level, live)?;
+                    lhs = apply_binary(op, lhs, rhs, live)?;
+                }
+                _ => {
+                    let rhs = self.binary(level + 1, live)?;
+                    lhs = apply_binary(op, lhs, rhs, live)?;
+                }
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self, live: bool) -> ShellResult<i64> {
+        match self.peek_op() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(i64::from(self.unary(live)? == 0))
+            }
+            Some("~") => {
+                self.pos += 1;
+                Ok(!self.unary(live)?)
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(self.unary(live)?.wrapping_neg())
+            }
+            Some("+") => {
+                self.pos += 1;
+                self.unary(live)
+            }
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Ident(name)) = self.next() else {
+                    return Err(arith_error(format!(
+                        "syntax error: operand expected after `{op}`"
+                    )));
+                };
+                let delta = if op == "++" { 1 } else { -1 };
+                let value = self.read_var(&name)?.wrapping_add(delta);
+                self.write_var(&name, value, live);
+                Ok(value)
+            }
+            _ => self.postfix(live),
+        }
+    }
+
+    fn postfix(&mut self, live: bool) -> ShellResult<i64> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(n),
+            Some(Token::Ident(name)) => {
+                let value = self.read_var(&name)?;
+                if let Some(op @ ("++" | "--")) = self.peek_op() {
+                    self.pos += 1;
+                    let delta = if op == "++" { 1 } else { -1 };
+                    self.write_var(&name, value.wrapping_add(delta), live);
+                }
+                Ok(value)
+            }
+            Some(Token::LParen) => {
+                let value = self.comma(live)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err(arith_error("syntax error: missing `)`")),
+                }
+            }
+            Some(other) => Err(arith_error(format!(
+                "syntax error: operand expected (error token is {other:?})"
+            ))),
+            None => Err(arith_error("syntax error: operand expected")),
+        }
+    }
+}
+
+/// Binding level of a binary operator (higher binds tighter)
+fn binary_level(op: &str) -> Option<usize> {
+    Some(match op {
+        "||" => 0,
+        "&&" => 1,
+        "|" => 2,
+        "^" => 3,
+        "&" => 4,
+        "==" | "!=" => 5,
+        "<" | ">" | "<=" | ">=" => 6,
+        "<<" | ">>" => 7,
+        "+" | "-" => 8,
+        "*" | "/" | "%" => 9,
+        "**" => 10,
+        _ => return None,
+    })
+}
+
+fn apply_binary(op: &str, lhs: i64, rhs: i64, live: bool) -> ShellResult<i64> {
+    Ok(match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" => {
+            if rhs == 0 {
+                if !live {
+                    return Ok(0);
+                }
+                return Err(ShellError::new(
+                    ErrorKind::RuntimeError(RuntimeErrorKind::DivisionByZero),
+                    "division by 0",
+                ));
+            }
+            if op == "/" {
+                lhs.wrapping_div(rhs)
+            } else {
+                lhs.wrapping_rem(rhs)
+            }
+        }
+        "**" => {
+            if rhs < 0 {
+                return Err(arith_error("exponent less than 0"));
+            }
+            lhs.wrapping_pow(u32::try_from(rhs).unwrap_or(u32::MAX))
+        }
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "&" => lhs & rhs,
+        "|" => lhs | rhs,
+        "^" => lhs ^ rhs,
+        "<" => i64::from(lhs < rhs),
+        ">" => i64::from(lhs > rhs),
+        "<=" => i64::from(lhs <= rhs),
+        ">=" => i64::from(lhs >= rhs),
+        "==" => i64::from(lhs == rhs),
+        "!=" => i64::from(lhs != rhs),
+        _ => return Err(arith_error(format!("unknown operator `{op}`"))),
+    })
+}
+
+fn evaluate_with_depth(expr: &str, ctx: &ShellContext, depth: usize) -> ShellResult<i64> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut evaluator = Evaluator {
+        tokens,
+        pos: 0,
+        ctx,
+        depth,
+    };
+    let value = evaluator.comma(true)?;
+    if let Some(tok) = evaluator.peek() {
+        return Err(arith_error(format!(
+            "syntax error in expression (error token is {tok:?})"
+        )));
+    }
+    Ok(value)
+}
+
+/// Evaluate an arithmetic expression against the shell's variables.
+///
+/// Assignments (`=`, `+=`, `++`, ...) are written back into `ctx`.
+/// An empty expression evaluates to 0.
+pub fn evaluate(expr: &str, ctx: &ShellContext) -> ShellResult<i64> {
+    evaluate_with_depth(expr, ctx, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence_and_assignment() {
+        let ctx = ShellContext::new();
+        assert_eq!(evaluate("1 + 2 * 3", &ctx).unwrap(), 7);
+        assert_eq!(evaluate("2 ** 3 ** 2", &ctx).unwrap(), 512);
+        assert_eq!(evaluate("i = 5, i += 2", &ctx).unwrap(), 7);
+        assert_eq!(ctx.get_var("i").as_deref(), Some("7"));
+        assert_eq!(evaluate("i++", &ctx).unwrap(), 7);
+        assert_eq!(evaluate("--i", &ctx).unwrap(), 7);
+        assert_eq!(evaluate("i < 10 ? 0x10 : 010", &ctx).unwrap(), 16);
+    }
+
+    #[test]
+    fn short_circuit_skips_side_effects() {
+        let ctx = ShellContext::new();
+        assert_eq!(evaluate("0 && (nxsh_arith_skipped = 1)", &ctx).unwrap(), 0);
+        assert_eq!(ctx.get_var("nxsh_arith_skipped"), None);
+        assert_eq!(evaluate("1 || 1 / 0", &ctx).unwrap(), 1);
+        assert!(evaluate("1 / 0", &ctx).is_err());
+    }
+}
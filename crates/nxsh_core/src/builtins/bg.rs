@@ -20,52 +20,32 @@ impl Builtin for BgBuiltin {
             )
         })?;
 
-        // Parse job specification
-        let job_id = if args.is_empty() {
-            // Use most recent stopped job
-            let stopped_jobs = job_manager_guard.get_stopped_jobs();
-            if stopped_jobs.is_empty() {
-                return Err(crate::error::ShellError::new(
-                    crate::error::ErrorKind::RuntimeError(
-                        crate::error::RuntimeErrorKind::InvalidArgument,
-                    ),
-                    "bg: no stopped job".to_string(),
-                ));
+        // Parse job specification; a bare number is a job number and no
+        // operand means the most recent stopped job
+        let job_id = match args.first() {
+            Some(spec) => {
+                let spec = if spec.starts_with('%') {
+                    spec.clone()
+                } else {
+                    format!("%{spec}")
+                };
+                job_manager_guard.resolve_job_spec(&spec).map_err(|e| {
+                    crate::error::ShellError::new(e.kind, format!("bg: {}", e.message))
+                })?
             }
-            stopped_jobs
-                .last()
+            None => job_manager_guard
+                .get_stopped_jobs()
+                .iter()
+                .map(|job| job.id)
+                .max()
                 .ok_or_else(|| {
                     crate::error::ShellError::new(
                         crate::error::ErrorKind::RuntimeError(
                             crate::error::RuntimeErrorKind::InvalidArgument,
                         ),
-                        "bg: no stopped job available".to_string(),
-                    )
-                })?
-                .id
-        } else {
-            let job_spec = &args[0];
-            if let Some(job_num_str) = job_spec.strip_prefix('%') {
-                // Parse job number
-                job_num_str.parse::<u32>().map_err(|_| {
-                    crate::error::ShellError::new(
-                        crate::error::ErrorKind::RuntimeError(
-                            crate::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        format!("bg: invalid job specification: {job_spec}"),
+                        "bg: no stopped job".to_string(),
                     )
-                })?
-            } else {
-                // Assume it's a job number without %
-                job_spec.parse::<u32>().map_err(|_| {
-                    crate::error::ShellError::new(
-                        crate::error::ErrorKind::RuntimeError(
-                            crate::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        format!("bg: invalid job specification: {job_spec}"),
-                    )
-                })?
-            }
+                })?,
         };
 
         // Check if job exists
@@ -91,8 +71,13 @@ impl Builtin for BgBuiltin {
         // Move job to background
         job_manager_guard.move_job_to_background(job_id)?;
 
-        // Get job description for output
-        let output = format!("[{}] {}", job_id, job.description);
+        // Report the resumed job as bash does: `[1]+ sleep 10 &`
+        let output = format!(
+            "[{}]{} {} &\n",
+            job_id,
+            job_manager_guard.job_marker(job_id),
+            job.description
+        );
 
         Ok(ExecutionResult::success(0).with_output(output.as_bytes().to_vec()))
     }
@@ -0,0 +1,109 @@
+//! disown built-in command implementation
+//!
+//! The disown command removes jobs from the job table. The processes keep
+//! running but are no longer listed, waited for or reported by the shell.
+
+use super::lock_jobs;
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::{Builtin, ExecutionResult};
+use crate::job::JobId;
+
+pub struct DisownBuiltin;
+
+impl Builtin for DisownBuiltin {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut all = false;
+        let mut running_only = false;
+        let mut specs = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-a" => all = true,
+                "-r" => running_only = true,
+                // The shell never sends SIGHUP to its jobs, so `-h` has nothing to mark
+                "-h" | "--" => {}
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    return Ok(ExecutionResult::failure(2).with_error(
+                        format!("disown: {flag}: invalid option\n{}\n", self.synopsis())
+                            .into_bytes(),
+                    ));
+                }
+                spec => specs.push(spec),
+            }
+        }
+
+        let job_manager = context.job_manager();
+        let mut manager = lock_jobs(&job_manager)?;
+        let mut errors = String::new();
+
+        let targets: Vec<JobId> = if !specs.is_empty() {
+            let mut ids = Vec::new();
+            for spec in specs {
+                let job_id = if spec.starts_with('%') {
+                    manager.resolve_job_spec(spec).ok()
+                } else {
+                    spec.parse().ok().and_then(|pid| manager.job_for_pid(pid))
+                };
+                match job_id {
+                    Some(job_id) => ids.push(job_id),
+                    None => errors.push_str(&format!("disown: {spec}: no such job\n")),
+                }
+            }
+            ids
+        } else if all || running_only {
+            manager
+                .get_all_jobs()
+                .into_iter()
+                .filter(|job| !running_only || job.is_running())
+                .map(|job| job.id)
+                .collect()
+        } else {
+            match manager.current_and_previous().0 {
+                Some(current) => vec![current],
+                None => {
+                    return Ok(ExecutionResult::failure(1)
+                        .with_error(b"disown: current: no such job\n".to_vec()));
+                }
+            }
+        };
+
+        for job_id in targets {
+            manager.remove_job(job_id);
+        }
+
+        if errors.is_empty() {
+            Ok(ExecutionResult::success(0))
+        } else {
+            Ok(ExecutionResult::failure(1).with_error(errors.into_bytes()))
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "disown"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove jobs from the job table"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "disown [-h] [-ar] [job_spec | pid ...]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove each given job from the table of active jobs. Without operands,\n\
+        remove the current job.\n\n\
+        Options:\n\
+        -a  Remove all jobs\n\
+        -r  Remove only running jobs\n\
+        -h  Accepted for compatibility; jobs are never sent SIGHUP"
+    }
+
+    fn usage(&self) -> &'static str {
+        "disown [-h] [-ar] [%n | pid ...]\n\n\
+        Examples:\n\
+        disown      # Forget the current job\n\
+        disown %2   # Forget job 2\n\
+        disown -a   # Forget every job"
+    }
+}
@@ -5,6 +5,14 @@
 use crate::context::ShellContext;
 use crate::error::ShellResult;
 use crate::executor::{Builtin, ExecutionResult};
+use crate::job::JobStatus;
+use std::io::Write;
+
+/// Signal number behind the exit status of a job stopped from the keyboard
+#[cfg(unix)]
+const STOP_SIGNAL: i32 = nix::sys::signal::Signal::SIGTSTP as i32;
+#[cfg(not(unix))]
+const STOP_SIGNAL: i32 = 20;
 
 pub struct FgBuiltin;
 
@@ -20,99 +28,61 @@ impl Builtin for FgBuiltin {
             )
         })?;
 
-        // Parse job specification
-        let job_id = if args.is_empty() {
-            // Use most recent job
-            let jobs = job_manager_guard.get_all_jobs();
-            if jobs.is_empty() {
-                return Err(crate::error::ShellError::new(
-                    crate::error::ErrorKind::RuntimeError(
-                        crate::error::RuntimeErrorKind::InvalidArgument,
-                    ),
-                    "fg: no current job".to_string(),
-                ));
-            }
-            jobs.last()
-                .ok_or_else(|| {
-                    crate::error::ShellError::new(
-                        crate::error::ErrorKind::RuntimeError(
-                            crate::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        "fg: no current job available".to_string(),
-                    )
-                })?
-                .id
-        } else {
-            let job_spec = &args[0];
-            if let Some(job_num_str) = job_spec.strip_prefix('%') {
-                // Parse job number
-                job_num_str.parse::<u32>().map_err(|_| {
-                    crate::error::ShellError::new(
-                        crate::error::ErrorKind::RuntimeError(
-                            crate::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        format!("fg: invalid job specification: {job_spec}"),
-                    )
-                })?
-            } else {
-                // Assume it's a job number without %
-                job_spec.parse::<u32>().map_err(|_| {
-                    crate::error::ShellError::new(
-                        crate::error::ErrorKind::RuntimeError(
-                            crate::error::RuntimeErrorKind::InvalidArgument,
-                        ),
-                        format!("fg: invalid job specification: {job_spec}"),
-                    )
-                })?
-            }
+        // Parse job specification; a bare number is a job number
+        let spec = match args.first() {
+            Some(spec) if spec.starts_with('%') => spec.clone(),
+            Some(number) => format!("%{number}"),
+            None => "%+".to_string(),
         };
+        let job_id = job_manager_guard
+            .resolve_job_spec(&spec)
+            .map_err(|e| crate::error::ShellError::new(e.kind, format!("fg: {}", e.message)))?;
 
-        // Check if job exists
-        if job_manager_guard.get_job(job_id)?.is_none() {
-            return Err(crate::error::ShellError::new(
-                crate::error::ErrorKind::RuntimeError(
-                    crate::error::RuntimeErrorKind::InvalidArgument,
-                ),
-                format!("fg: job {job_id} not found"),
-            ));
-        }
-
-        // Move job to foreground
-        job_manager_guard.move_job_to_foreground(job_id)?;
-
-        // Get job description for output
+        // Hand the terminal to the job before it continues
         let job = job_manager_guard.get_job(job_id)?.ok_or_else(|| {
             crate::error::ShellError::new(
                 crate::error::ErrorKind::RuntimeError(
                     crate::error::RuntimeErrorKind::InvalidArgument,
                 ),
-                format!("fg: job {job_id} not found after move"),
+                format!("fg: job {job_id} not found"),
             )
         })?;
-        let output = job.description.to_string();
+        if let Some(terminal) = &context.terminal {
+            if job.pgid != 0 {
+                let _ = terminal.give_to(job.pgid);
+            }
+        }
+        // Echo the command line before it takes over the terminal
+        let _ = writeln!(context.stdout, "{}", job.description);
+        let _ = context.stdout.flush();
 
-        // Wait for job completion
-        drop(job_manager_guard); // Release lock before waiting
-        let job_manager_for_wait = context.job_manager();
-        let job_manager_wait_guard = job_manager_for_wait.lock().map_err(|_| {
-            crate::error::ShellError::new(
-                crate::error::ErrorKind::InternalError(
-                    crate::error::InternalErrorKind::InvalidState,
-                ),
-                "Job manager lock poisoned".to_string(),
-            )
-        })?;
+        // Move job to foreground, resuming it if stopped
+        job_manager_guard.move_job_to_foreground(job_id)?;
+        let status = job_manager_guard.wait_for_foreground_job(job_id);
+        if let Some(terminal) = &context.terminal {
+            let _ = terminal.reclaim();
+        }
 
-        let final_status = job_manager_wait_guard.wait_for_job(job_id)?;
+        // Stopped again: the job stays in the table
+        if status? == JobStatus::Stopped {
+            job_manager_guard.with_job_mut(job_id, |job| job.move_to_background());
+            let notice = match job_manager_guard.get_job(job_id)? {
+                Some(job) => format!(
+                    "\n{}\n",
+                    job.status_line(job_manager_guard.job_marker(job_id))
+                ),
+                None => String::new(),
+            };
+            return Ok(ExecutionResult::failure(128 + STOP_SIGNAL).with_error(notice.into_bytes()));
+        }
 
-        // Return with appropriate exit code
-        let exit_code = match final_status {
-            crate::job::JobStatus::Done(code) => code,
-            crate::job::JobStatus::Terminated(_) => 128 + 15, // 128 + SIGTERM
-            _ => 0,
-        };
+        // A job that ran to completion in the foreground is not reported later
+        let exit_code = job_manager_guard
+            .remove_job(job_id)
+            .and_then(|job| job.exit_code())
+            .unwrap_or(0);
 
-        Ok(ExecutionResult::success(exit_code).with_output(output.as_bytes().to_vec()))
+        Ok(ExecutionResult::success(exit_code))
     }
 
     fn name(&self) -> &'static str {
@@ -0,0 +1,198 @@
+//! hash and rehash built-in command implementation
+//!
+//! The shell remembers where it found each external command so later runs
+//! skip the `$PATH` search. `hash` shows and edits that table; `rehash`
+//! empties it, e.g. after installing a program that shadows another.
+
+use crate::context::ShellContext;
+use crate::error::{ErrorKind, InternalErrorKind, ShellError, ShellResult};
+use crate::executor::{Builtin, ExecutionResult};
+use nxsh_hal::command::PathCache;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::MutexGuard;
+
+pub struct HashBuiltin;
+
+fn lock_hash(context: &ShellContext) -> ShellResult<MutexGuard<'_, PathCache>> {
+    context.command_hash.lock().map_err(|_| {
+        ShellError::new(
+            ErrorKind::InternalError(InternalErrorKind::InvalidState),
+            "Command hash lock poisoned".to_string(),
+        )
+    })
+}
+
+impl Builtin for HashBuiltin {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let mut reset = false;
+        let mut delete = false;
+        let mut print_paths = false;
+        let mut reusable = false;
+        let mut given_path: Option<String> = None;
+        let mut names = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    names.extend(iter.by_ref().cloned());
+                }
+                "-p" => match iter.next() {
+                    Some(path) => given_path = Some(path.clone()),
+                    None => {
+                        return Ok(ExecutionResult::failure(2).with_error(
+                            format!(
+                                "hash: -p: option requires an argument\n{}\n",
+                                self.synopsis()
+                            )
+                            .into_bytes(),
+                        ));
+                    }
+                },
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    for c in flag[1..].chars() {
+                        match c {
+                            'r' => reset = true,
+                            'd' => delete = true,
+                            't' => print_paths = true,
+                            'l' => reusable = true,
+                            _ => {
+                                return Ok(ExecutionResult::failure(2).with_error(
+                                    format!("hash: -{c}: invalid option\n{}\n", self.synopsis())
+                                        .into_bytes(),
+                                ));
+                            }
+                        }
+                    }
+                }
+                name => names.push(name.to_string()),
+            }
+        }
+
+        let path_var = context.get_var("PATH").unwrap_or_default();
+        let mut cache = lock_hash(context)?;
+        if reset {
+            cache.clear();
+        }
+
+        let mut output = String::new();
+        let mut errors = String::new();
+        if let Some(path) = given_path {
+            for name in &names {
+                cache.insert(name, PathBuf::from(&path));
+            }
+        } else if delete {
+            for name in &names {
+                if !cache.remove(name) {
+                    errors.push_str(&format!("hash: {name}: not found\n"));
+                }
+            }
+        } else if print_paths {
+            for name in &names {
+                match cache.remember(name, OsStr::new(&path_var)) {
+                    Some(found) if names.len() > 1 => {
+                        output.push_str(&format!("{name}\t{}\n", found.display()))
+                    }
+                    Some(found) => output.push_str(&format!("{}\n", found.display())),
+                    None => errors.push_str(&format!("hash: {name}: not found\n")),
+                }
+            }
+        } else if !names.is_empty() {
+            for name in &names {
+                if cache.remember(name, OsStr::new(&path_var)).is_none() {
+                    errors.push_str(&format!("hash: {name}: not found\n"));
+                }
+            }
+        } else if !reset {
+            if cache.is_empty() {
+                output.push_str("hash: hash table empty\n");
+            } else if reusable {
+                for (name, entry) in cache.entries() {
+                    output.push_str(&format!(
+                        "builtin hash -p {} {name}\n",
+                        entry.path.display()
+                    ));
+                }
+            } else {
+                output.push_str("hits\tcommand\n");
+                for (_, entry) in cache.entries() {
+                    output.push_str(&format!("{:>4}\t{}\n", entry.hits, entry.path.display()));
+                }
+            }
+        }
+
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        Ok(ExecutionResult::success(exit_code)
+            .with_output(output.into_bytes())
+            .with_error(errors.into_bytes()))
+    }
+
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remember or display command locations"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "hash [-lr] [-p pathname] [-dt] [name ...]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Look up each name in $PATH and remember its location. Without names,\n\
+        list the remembered commands with the number of times each was run.\n\
+        The table is emptied whenever $PATH changes.\n\n\
+        Options:\n\
+        -r           Forget all remembered locations\n\
+        -d           Forget the locations of the given names\n\
+        -t           Print the remembered location of each name\n\
+        -l           List the table in a form that can be reused as input\n\
+        -p pathname  Use pathname as the location of the given names"
+    }
+
+    fn usage(&self) -> &'static str {
+        "hash [-lr] [-p pathname] [-dt] [name ...]\n\n\
+        Examples:\n\
+        hash            # Show remembered commands and hit counts\n\
+        hash -t git     # Print where git is run from\n\
+        hash -r         # Forget everything"
+    }
+}
+
+pub struct RehashBuiltin;
+
+impl Builtin for RehashBuiltin {
+    fn execute(
+        &self,
+        context: &mut ShellContext,
+        _args: &[String],
+    ) -> ShellResult<ExecutionResult> {
+        lock_hash(context)?.clear();
+        Ok(ExecutionResult::success(0))
+    }
+
+    fn name(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn help(&self) -> &'static str {
+        "Forget remembered command locations"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn description(&self) -> &'static str {
+        "Empty the command location table, like `hash -r`, so every command\n\
+        is looked up in $PATH again."
+    }
+
+    fn usage(&self) -> &'static str {
+        "rehash\n\n\
+        Examples:\n\
+        rehash   # Pick up programs installed earlier in $PATH"
+    }
+}
@@ -2,84 +2,69 @@
 //!
 //! The jobs command lists active jobs in the shell.
 
+use super::lock_jobs;
 use crate::context::ShellContext;
 use crate::error::ShellResult;
 use crate::executor::{Builtin, ExecutionResult};
-use crate::job::JobStatus;
 
 pub struct JobsBuiltin;
 
 impl Builtin for JobsBuiltin {
     fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
         let job_manager = context.job_manager();
-        let job_manager_guard = job_manager.lock().map_err(|_| {
-            crate::error::ShellError::new(
-                crate::error::ErrorKind::InternalError(
-                    crate::error::InternalErrorKind::InvalidState,
-                ),
-                "Job manager lock poisoned".to_string(),
-            )
-        })?;
+        let mut job_manager_guard = lock_jobs(&job_manager)?;
 
-        let jobs = job_manager_guard.get_all_jobs();
-
-        let mut output = String::new();
-
-        // Parse options
+        // Parse options; remaining operands select jobs by spec
         let show_pids = args.contains(&"-p".to_string());
         let show_long = args.contains(&"-l".to_string());
-
-        if jobs.is_empty() {
-            // No jobs to display
-            return Ok(ExecutionResult::success(0));
-        }
-
-        for job in jobs {
-            let status_str = match &job.status {
-                JobStatus::Running => "Running",
-                JobStatus::Background => "Running",
-                JobStatus::Foreground => "Running",
-                JobStatus::Stopped => "Stopped",
-                JobStatus::Waiting => "Waiting",
-                JobStatus::Done(code) => {
-                    if *code == 0 {
-                        "Done"
-                    } else {
-                        "Exit"
-                    }
+        let mut errors = String::new();
+        let mut jobs = Vec::new();
+        let specs: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+        if specs.is_empty() {
+            jobs = job_manager_guard.get_all_jobs();
+        } else {
+            for spec in specs {
+                match job_manager_guard.resolve_job_spec(spec) {
+                    Ok(job_id) => jobs.extend(job_manager_guard.get_job(job_id)?),
+                    Err(e) => errors.push_str(&format!("jobs: {}\n", e.message)),
                 }
-                JobStatus::Failed(_) => "Failed",
-                JobStatus::Terminated(_) => "Terminated",
-            };
+            }
+        }
+        jobs.sort_by_key(|job| job.id);
 
+        let mut output = String::new();
+        for job in &jobs {
+            let marker = job_manager_guard.job_marker(job.id);
             if show_pids {
                 // Show process IDs
-                for process in &job.processes {
+                for process in job.processes.iter().filter(|p| p.pid != 0) {
                     output.push_str(&format!("{}\n", process.pid));
                 }
             } else if show_long {
-                // Show detailed information
-                for process in &job.processes {
-                    output.push_str(&format!(
-                        "[{}] {} {} {} {}\n",
-                        job.id,
-                        process.pid,
-                        status_str,
-                        if job.foreground { "+" } else { " " },
-                        job.description
-                    ));
-                }
-            } else {
-                // Standard format
-                let foreground_indicator = if job.foreground { "+" } else { " " };
+                // Show the leader's process ID before the status
+                let pid = job.processes.first().map_or(0, |p| p.pid);
+                let suffix = if job.is_running() { " &" } else { "" };
                 output.push_str(&format!(
-                    "[{}]{} {} {}\n",
-                    job.id, foreground_indicator, status_str, job.description
+                    "[{}]{marker} {pid} {:<24}{}{suffix}\n",
+                    job.id,
+                    job.state_label(),
+                    job.description
                 ));
+            } else {
+                output.push_str(&job.status_line(marker));
+                output.push('\n');
             }
         }
 
-        Ok(ExecutionResult::success(0).with_output(output.trim().as_bytes().to_vec()))
+        // Finished jobs are reported once, here or as a completion notice
+        for job in jobs.iter().filter(|job| job.is_finished()) {
+            job_manager_guard.remove_job(job.id);
+        }
+
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        Ok(ExecutionResult::success(exit_code)
+            .with_output(output.into_bytes())
+            .with_error(errors.into_bytes()))
     }
 
     fn name(&self) -> &'static str {
@@ -91,7 +76,7 @@ impl Builtin for JobsBuiltin {
     }
 
     fn synopsis(&self) -> &'static str {
-        "jobs [-lp]"
+        "jobs [-lp] [job_spec ...]"
     }
 
     fn description(&self) -> &'static str {
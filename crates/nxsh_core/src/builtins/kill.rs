@@ -7,6 +7,7 @@
 //! - Process validation and error handling
 //! - Cross-platform compatibility
 
+use super::lock_jobs;
 use crate::context::ShellContext;
 use crate::error::ShellResult;
 use crate::executor::{Builtin, ExecutionMetrics, ExecutionResult, ExecutionStrategy};
@@ -29,14 +30,10 @@ impl Builtin for KillBuiltin {
     }
 
     fn usage(&self) -> &'static str {
-        "kill [-s SIGNAL | -SIGNAL] PID...\nkill -l [SIGNAL]"
+        "kill [-s SIGNAL | -n NUM | -SIGNAL] PID|%JOB...\nkill -l [SIGNAL|STATUS]..."
     }
 
-    fn execute(
-        &self,
-        _context: &mut ShellContext,
-        args: &[String],
-    ) -> ShellResult<ExecutionResult> {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
         let start_time = Instant::now();
 
         if args.is_empty() {
@@ -51,7 +48,7 @@ impl Builtin for KillBuiltin {
         }
 
         // Parse arguments
-        let mut signal = "TERM"; // Default signal
+        let mut signal_num = 15; // SIGTERM
         let mut list_signals = false;
         let mut pids = Vec::new();
         let mut i = 0;
@@ -61,30 +58,41 @@ impl Builtin for KillBuiltin {
 
             if arg == "-l" || arg == "--list" {
                 list_signals = true;
-            } else if let Some(stripped) = arg.strip_prefix("-s") {
-                // -s SIGNAL format
-                if arg.len() > 2 {
-                    signal = stripped;
-                } else if i + 1 < args.len() {
-                    i += 1;
-                    signal = &args[i];
-                } else {
-                    return Ok(ExecutionResult {
-                        exit_code: 1,
-                        stdout: String::new(),
-                        stderr: "kill: option requires an argument -- s".to_string(),
-                        execution_time: start_time.elapsed().as_micros() as u64,
-                        strategy: ExecutionStrategy::DirectInterpreter,
-                        metrics: ExecutionMetrics::default(),
-                    });
-                }
-            } else if arg.starts_with('-') && arg.len() > 1 && arg != "--" {
-                // -SIGNAL format (e.g., -9, -KILL)
-                signal = &arg[1..];
             } else if arg == "--" {
                 // End of options
                 i += 1;
                 break;
+            } else if !pids.is_empty() || list_signals {
+                // Options end at the first PID, so `-1` after one is a
+                // process group rather than a signal
+                pids.push(arg.clone());
+            } else if let Some(spec) = arg
+                .strip_prefix("--signal")
+                .or_else(|| arg.strip_prefix("-s"))
+                .or_else(|| arg.strip_prefix("-n"))
+            {
+                // -s SIGNAL, -sSIGNAL or -n NUMBER
+                let spec = if !spec.is_empty() {
+                    spec.trim_start_matches('=').to_string()
+                } else if i + 1 < args.len() {
+                    i += 1;
+                    args[i].clone()
+                } else {
+                    return Ok(Self::failure(
+                        start_time,
+                        format!("kill: {arg}: option requires an argument\n"),
+                    ));
+                };
+                match nxsh_hal::signal::parse_signal(&spec) {
+                    Some(num) => signal_num = num,
+                    None => return Ok(Self::invalid_signal(start_time, &spec)),
+                }
+            } else if let Some(spec) = arg.strip_prefix('-').filter(|spec| !spec.is_empty()) {
+                // -SIGNAL format (e.g., -9, -KILL, -SIGHUP)
+                match nxsh_hal::signal::parse_signal(spec) {
+                    Some(num) => signal_num = num,
+                    None => return Ok(Self::invalid_signal(start_time, spec)),
+                }
             } else {
                 // PID or job spec
                 pids.push(arg.clone());
@@ -99,56 +107,53 @@ impl Builtin for KillBuiltin {
         }
 
         if list_signals {
-            let signal_list = self.get_signal_list();
-            return Ok(ExecutionResult {
-                exit_code: 0,
-                stdout: signal_list,
-                stderr: String::new(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
+            return Ok(self.list_signals(start_time, &pids));
         }
 
         if pids.is_empty() {
-            return Ok(ExecutionResult {
-                exit_code: 1,
-                stdout: String::new(),
-                stderr: "kill: no process ID specified".to_string(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
+            return Ok(Self::failure(
+                start_time,
+                "kill: no process ID specified\n".to_string(),
+            ));
         }
 
-        // Convert signal name/number to system signal
-        let signal_num = match self.parse_signal(signal) {
-            Ok(num) => num,
-            Err(err) => {
-                return Ok(ExecutionResult {
-                    exit_code: 1,
-                    stdout: String::new(),
-                    stderr: format!("kill: {err}"),
-                    execution_time: start_time.elapsed().as_micros() as u64,
-                    strategy: ExecutionStrategy::DirectInterpreter,
-                    metrics: ExecutionMetrics::default(),
-                });
-            }
-        };
-
         // Send signals to processes
         let mut failed_pids = Vec::new();
         let mut stdout_lines = Vec::new();
 
+        let job_manager = context.job_manager();
         for pid_str in &pids {
-            match self.kill_process(pid_str, signal_num) {
-                Ok(message) => {
-                    if !message.is_empty() {
-                        stdout_lines.push(message);
+            // A `%n` job spec signals every live process of the job
+            let targets: Vec<String> = if pid_str.starts_with('%') {
+                let manager = lock_jobs(&job_manager)?;
+                match manager
+                    .resolve_job_spec(pid_str)
+                    .and_then(|job_id| manager.get_job(job_id))
+                {
+                    Ok(Some(job)) => job
+                        .processes
+                        .iter()
+                        .filter(|p| p.pid != 0 && !p.is_finished())
+                        .map(|p| p.pid.to_string())
+                        .collect(),
+                    _ => {
+                        failed_pids.push(format!("kill: {pid_str}: no such job"));
+                        continue;
                     }
                 }
-                Err(err) => {
-                    failed_pids.push(format!("kill: {pid_str}: {err}"));
+            } else {
+                vec![pid_str.clone()]
+            };
+            for target in targets {
+                match self.kill_process(&target, signal_num) {
+                    Ok(message) => {
+                        if !message.is_empty() {
+                            stdout_lines.push(message);
+                        }
+                    }
+                    Err(err) => {
+                        failed_pids.push(format!("kill: {pid_str}: {err}"));
+                    }
                 }
             }
         }
@@ -181,13 +186,16 @@ impl Builtin for KillBuiltin {
         "kill - terminate processes by PID or job ID
 
 USAGE:
-    kill [-s SIGNAL | -SIGNAL] PID...
-    kill -l [SIGNAL]
+    kill [-s SIGNAL | -n NUM | -SIGNAL] PID|%JOB...
+    kill -l [SIGNAL|STATUS]...
 
 OPTIONS:
     -s SIGNAL    Send the specified signal (default: TERM)
-    -SIGNAL      Send the specified signal by name or number
-    -l, --list   List available signal names
+    -n NUM       Send the signal with this number
+    -SIGNAL      Send the specified signal by name or number, with or
+                 without the SIG prefix
+    -l, --list   List available signals, or translate each SIGNAL name to
+                 its number and each number or exit STATUS to its name
 
 SIGNALS:
     TERM (15)    Terminate gracefully (default)
@@ -202,100 +210,69 @@ EXAMPLES:
     kill 1234           # Send TERM signal to process 1234
     kill -9 1234        # Force kill process 1234
     kill -s HUP 1234    # Send HUP signal to process 1234
-    kill -l             # List all available signals"
+    kill -SIGUSR1 %1    # Send USR1 signal to job 1
+    kill -l             # List all available signals
+    kill -l 137         # Name the signal that ended a process: KILL"
     }
 }
 
 impl KillBuiltin {
-    /// Get list of available signals
-    fn get_signal_list(&self) -> String {
-        let signals = [
-            ("HUP", 1),
-            ("INT", 2),
-            ("QUIT", 3),
-            ("ILL", 4),
-            ("TRAP", 5),
-            ("ABRT", 6),
-            ("BUS", 7),
-            ("FPE", 8),
-            ("KILL", 9),
-            ("USR1", 10),
-            ("SEGV", 11),
-            ("USR2", 12),
-            ("PIPE", 13),
-            ("ALRM", 14),
-            ("TERM", 15),
-            ("STKFLT", 16),
-            ("CHLD", 17),
-            ("CONT", 18),
-            ("STOP", 19),
-            ("TSTP", 20),
-            ("TTIN", 21),
-            ("TTOU", 22),
-            ("URG", 23),
-            ("XCPU", 24),
-            ("XFSZ", 25),
-            ("VTALRM", 26),
-            ("PROF", 27),
-            ("WINCH", 28),
-            ("IO", 29),
-            ("PWR", 30),
-            ("SYS", 31),
-        ];
-
-        let mut output = String::new();
-        for (name, num) in &signals {
-            output.push_str(&format!("{num:2}) {name}\n"));
+    /// A failed run that printed `stderr`
+    fn failure(start_time: Instant, stderr: String) -> ExecutionResult {
+        ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr,
+            execution_time: start_time.elapsed().as_micros() as u64,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics::default(),
         }
-        output
     }
 
-    /// Parse signal name or number to signal number
-    fn parse_signal(&self, signal: &str) -> Result<i32, String> {
-        // Try to parse as number first
-        if let Ok(num) = signal.parse::<i32>() {
-            if num > 0 && num <= 31 {
-                return Ok(num);
-            } else {
-                return Err(format!("invalid signal number: {signal}"));
+    fn invalid_signal(start_time: Instant, spec: &str) -> ExecutionResult {
+        Self::failure(
+            start_time,
+            format!("kill: {spec}: invalid signal specification\n"),
+        )
+    }
+
+    /// `kill -l`: every signal, or with arguments the name of each number
+    /// and the number of each name. A number above 128 is read as the exit
+    /// status of a process killed by that signal less 128.
+    fn list_signals(&self, start_time: Instant, specs: &[String]) -> ExecutionResult {
+        let mut stdout = String::new();
+        if specs.is_empty() {
+            for (num, name) in nxsh_hal::signal::signal_table() {
+                stdout.push_str(&format!("{num:2}) SIG{name}\n"));
             }
         }
-
-        // Parse as signal name
-        let signal_upper = signal.to_uppercase();
-        match signal_upper.as_str() {
-            "HUP" => Ok(1),
-            "INT" => Ok(2),
-            "QUIT" => Ok(3),
-            "ILL" => Ok(4),
-            "TRAP" => Ok(5),
-            "ABRT" | "IOT" => Ok(6),
-            "BUS" => Ok(7),
-            "FPE" => Ok(8),
-            "KILL" => Ok(9),
-            "USR1" => Ok(10),
-            "SEGV" => Ok(11),
-            "USR2" => Ok(12),
-            "PIPE" => Ok(13),
-            "ALRM" => Ok(14),
-            "TERM" => Ok(15),
-            "STKFLT" => Ok(16),
-            "CHLD" | "CLD" => Ok(17),
-            "CONT" => Ok(18),
-            "STOP" => Ok(19),
-            "TSTP" => Ok(20),
-            "TTIN" => Ok(21),
-            "TTOU" => Ok(22),
-            "URG" => Ok(23),
-            "XCPU" => Ok(24),
-            "XFSZ" => Ok(25),
-            "VTALRM" => Ok(26),
-            "PROF" => Ok(27),
-            "WINCH" => Ok(28),
-            "IO" | "POLL" => Ok(29),
-            "PWR" => Ok(30),
-            "SYS" => Ok(31),
-            _ => Err(format!("invalid signal name: {signal}")),
+        for spec in specs {
+            let line = match spec.parse::<i32>() {
+                Ok(status) => {
+                    let num = if status > 128 { status - 128 } else { status };
+                    nxsh_hal::signal::signal_name(num).map(str::to_string)
+                }
+                Err(_) => nxsh_hal::signal::parse_signal(spec).map(|num| num.to_string()),
+            };
+            match line {
+                Some(line) => {
+                    stdout.push_str(&line);
+                    stdout.push('\n');
+                }
+                None => {
+                    let mut result = Self::invalid_signal(start_time, spec);
+                    result.stdout = stdout;
+                    return result;
+                }
+            }
+        }
+        ExecutionResult {
+            exit_code: 0,
+            stdout,
+            stderr: String::new(),
+            execution_time: start_time.elapsed().as_micros() as u64,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics::default(),
         }
     }
 
@@ -3,15 +3,22 @@
 //! This module provides implementations of shell built-in commands,
 //! including job control commands like jobs, fg, bg, etc.
 
+use crate::error::{ErrorKind, InternalErrorKind, ShellError, ShellResult};
 use crate::executor::Builtin;
-use std::sync::Arc;
+use crate::job::JobManager;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 pub mod bg;
+pub mod disown;
 pub mod fg;
+pub mod hash;
 pub mod id;
 pub mod jobs;
 pub mod kill;
+pub mod parallel;
+pub mod renice;
 pub mod testutils;
+pub mod wait;
 
 pub use id::IdBuiltin;
 use kill::KillBuiltin;
@@ -23,10 +30,28 @@ pub fn register_all_builtins() -> Vec<Arc<dyn Builtin>> {
         Arc::new(jobs::JobsBuiltin),
         Arc::new(fg::FgBuiltin),
         Arc::new(bg::BgBuiltin),
+        Arc::new(wait::WaitBuiltin),
+        Arc::new(parallel::ParallelBuiltin),
+        Arc::new(disown::DisownBuiltin),
+        Arc::new(hash::HashBuiltin),
+        Arc::new(hash::RehashBuiltin),
         Arc::new(IdBuiltin),
         Arc::new(ArgDumpBuiltin),
         Arc::new(KillBuiltin),
+        Arc::new(renice::ReniceBuiltin),
         // Minimal echo builtin to ensure tests relying on `echo` run under strict timeout env
         Arc::new(testutils::EchoBuiltin),
     ]
 }
+
+/// Lock the shell's job manager for a job control builtin
+pub(crate) fn lock_jobs(
+    job_manager: &Mutex<JobManager>,
+) -> ShellResult<MutexGuard<'_, JobManager>> {
+    job_manager.lock().map_err(|_| {
+        ShellError::new(
+            ErrorKind::InternalError(InternalErrorKind::InvalidState),
+            "Job manager lock poisoned".to_string(),
+        )
+    })
+}
@@ -0,0 +1,441 @@
+//! parallel built-in command implementation
+//!
+//! A subset of GNU parallel: run a command once for each argument, several
+//! at a time, each run a background job of the shell. A job's output is
+//! kept in one piece, so the output of different runs never interleaves.
+
+use super::lock_jobs;
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::{Builtin, ExecutionResult, Executor};
+use crate::job::{JobId, JobSignal, JobStatus};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the builtin re-checks its running jobs
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Highest exit status counting failed jobs, as GNU parallel caps it
+const MAX_FAILURE_STATUS: i32 = 101;
+
+/// The replacement strings, longest first so `{/.}` is not read as `{/}`
+const REPLACEMENTS: [&str; 6] = ["{/.}", "{//}", "{/}", "{.}", "{#}", "{}"];
+
+pub struct ParallelBuiltin;
+
+/// What happens to the remaining jobs once one fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Halt {
+    /// Run every job regardless
+    Never,
+    /// Start no more jobs but let the running ones finish
+    Soon,
+    /// Also terminate the running jobs
+    Now,
+}
+
+#[derive(Debug)]
+struct Options {
+    jobs: usize,
+    keep_order: bool,
+    joblog: Option<String>,
+    halt: Halt,
+    dry_run: bool,
+    command: Vec<String>,
+    /// Arguments after `:::`; without them lines of stdin are read
+    arguments: Option<Vec<String>>,
+}
+
+/// A started job and the threads collecting its output
+struct Running {
+    seq: usize,
+    job_id: JobId,
+    line: String,
+    started: SystemTime,
+    stdout: JoinHandle<Vec<u8>>,
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+/// A job that has finished, held back under `-k` until those before it are
+/// printed
+#[derive(Default)]
+struct Finished {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Builtin for ParallelBuiltin {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let options = match parse_options(args) {
+            Ok(options) => options,
+            Err(message) => {
+                return Ok(ExecutionResult::failure(2)
+                    .with_error(format!("parallel: {message}\n").into_bytes()))
+            }
+        };
+        let arguments = match options.arguments.clone() {
+            Some(arguments) => arguments,
+            None => BufReader::new(&mut context.stdin)
+                .lines()
+                .map_while(Result::ok)
+                .collect(),
+        };
+        let mut joblog = match &options.joblog {
+            Some(path) => match File::create(context.cwd.join(path)) {
+                Ok(mut file) => {
+                    let _ = writeln!(
+                        file,
+                        "Seq\tHost\tStarttime\tJobRuntime\tSend\tReceive\tExitval\tSignal\tCommand"
+                    );
+                    Some(file)
+                }
+                Err(e) => {
+                    return Ok(ExecutionResult::failure(2)
+                        .with_error(format!("parallel: {path}: {e}\n").into_bytes()))
+                }
+            },
+            None => None,
+        };
+
+        let job_manager = context.job_manager();
+        let mut pending = arguments.iter().enumerate().map(|(i, arg)| (i + 1, arg));
+        let mut running: Vec<Running> = Vec::new();
+        let mut held: BTreeMap<usize, Finished> = BTreeMap::new();
+        let mut next_to_print = 1;
+        let mut failures = 0;
+        let mut halted: Option<i32> = None;
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        loop {
+            while halted.is_none() && running.len() < options.jobs {
+                let Some((seq, arg)) = pending.next() else {
+                    break;
+                };
+                let words = command_words(&options.command, arg, seq);
+                if words.is_empty() {
+                    held.insert(seq, Finished::default());
+                    continue;
+                }
+                if options.dry_run {
+                    stdout.extend_from_slice(format!("{}\n", words.join(" ")).as_bytes());
+                    continue;
+                }
+                running.push(start_job(context, words, seq)?);
+            }
+            if running.is_empty() {
+                break;
+            }
+
+            let done: Vec<(Running, JobStatus)> = {
+                let mut manager = lock_jobs(&job_manager)?;
+                let mut done = Vec::new();
+                let mut index = 0;
+                while index < running.len() {
+                    match manager.get_job(running[index].job_id)? {
+                        Some(job) if !job.is_finished() => index += 1,
+                        job => {
+                            manager.remove_job(running[index].job_id);
+                            let status = job.map_or(JobStatus::Done(127), |job| job.status);
+                            done.push((running.remove(index), status));
+                        }
+                    }
+                }
+                done
+            };
+            if done.is_empty() {
+                if context.is_timed_out() {
+                    halted.get_or_insert(124);
+                    terminate(context, &running)?;
+                }
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+
+            for (job, status) in done {
+                let runtime = job.started.elapsed().unwrap_or_default();
+                let output = job.stdout.join().unwrap_or_default();
+                let mut errors = job.stderr.join().unwrap_or_default();
+                let (exit_code, signal) = match &status {
+                    JobStatus::Done(code) => (*code, 0),
+                    JobStatus::Terminated(sig) => (128 + sig, *sig),
+                    JobStatus::Failed(message) => {
+                        errors.extend_from_slice(format!("parallel: {message}\n").as_bytes());
+                        (127, 0)
+                    }
+                    _ => (0, 0),
+                };
+                if let Some(file) = joblog.as_mut() {
+                    let started = job
+                        .started
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64();
+                    let _ = writeln!(
+                        file,
+                        "{}\t:\t{started:.3}\t{:.3}\t0\t{}\t{exit_code}\t{signal}\t{}",
+                        job.seq,
+                        runtime.as_secs_f64(),
+                        output.len(),
+                        job.line
+                    );
+                }
+                if exit_code != 0 {
+                    failures += 1;
+                    if options.halt != Halt::Never && halted.is_none() {
+                        halted = Some(exit_code);
+                        if options.halt == Halt::Now {
+                            terminate(context, &running)?;
+                        }
+                    }
+                }
+                held.insert(
+                    job.seq,
+                    Finished {
+                        stdout: output,
+                        stderr: errors,
+                    },
+                );
+            }
+
+            // Jobs are printed as they finish, or in argument order with -k
+            let ready: Vec<usize> = if options.keep_order {
+                let mut ready = Vec::new();
+                while held.contains_key(&next_to_print) {
+                    ready.push(next_to_print);
+                    next_to_print += 1;
+                }
+                ready
+            } else {
+                held.keys().copied().collect()
+            };
+            for seq in ready {
+                if let Some(finished) = held.remove(&seq) {
+                    stdout.extend(finished.stdout);
+                    stderr.extend(finished.stderr);
+                }
+            }
+        }
+        // Under -k, jobs never run after a halt leave gaps in the order
+        for finished in held.into_values() {
+            stdout.extend(finished.stdout);
+            stderr.extend(finished.stderr);
+        }
+
+        let exit_code = halted.unwrap_or(failures.min(MAX_FAILURE_STATUS));
+        Ok(ExecutionResult::success(exit_code)
+            .with_output(stdout)
+            .with_error(stderr))
+    }
+
+    fn name(&self) -> &'static str {
+        "parallel"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run a command for each argument, several at a time"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "parallel [-j N] [-k] [--joblog FILE] [--halt WHEN] [--dry-run] command [args] [::: arguments]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run command once for each argument as background jobs of the shell,\n\
+        up to N at a time. `{}` in the command is replaced by the argument,\n\
+        which is appended when no replacement string appears. Without `:::`\n\
+        the arguments are the lines of standard input. The output of each job\n\
+        is kept together. The exit status is the number of failed jobs, at\n\
+        most 101, or with --halt the status of the job that failed.\n\n\
+        Replacement strings:\n\
+        {}    the argument          {.}   without its extension\n\
+        {/}   its last component    {//}  its directory\n\
+        {/.}  last component without extension\n\
+        {#}   the job's sequence number\n\n\
+        Options:\n\
+        -j, --jobs N    Run at most N jobs at once (0: no limit; default: one per CPU)\n\
+        -k, --keep-order\n\
+        \x20               Print output in argument order rather than as jobs finish\n\
+        --joblog FILE   Log each job's start, runtime and exit status to FILE\n\
+        --halt WHEN     After a failure: never (default), soon to start no more\n\
+        \x20               jobs, now to also terminate the running ones\n\
+        --dry-run       Print the commands instead of running them"
+    }
+
+    fn usage(&self) -> &'static str {
+        "parallel [options] command [args] ::: arguments...\n\n\
+        Examples:\n\
+        parallel -j4 gzip ::: *.log          # Compress four files at a time\n\
+        parallel convert {} {.}.png ::: *.jpg\n\
+        ls | parallel -k wc -l               # Arguments from standard input\n\
+        parallel --halt now make ::: a b c"
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        jobs: thread::available_parallelism().map_or(1, usize::from),
+        keep_order: false,
+        joblog: None,
+        halt: Halt::Never,
+        dry_run: false,
+        command: Vec::new(),
+        arguments: None,
+    };
+    let mut words = args.iter();
+    while let Some(arg) = words.as_slice().first() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => match arg.strip_prefix("-j").or_else(|| arg.strip_prefix("-P")) {
+                Some(value) if !value.is_empty() => ("-j", Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            },
+        };
+        if !name.starts_with('-') || name == "-" {
+            break;
+        }
+        words.next();
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| words.next().cloned())
+                .ok_or_else(|| format!("{name}: option requires an argument"))
+        };
+        match name {
+            "--" => break,
+            "-k" | "--keep-order" => options.keep_order = true,
+            "--dry-run" => options.dry_run = true,
+            "-j" | "-P" | "--jobs" => {
+                let value = value()?;
+                options.jobs = match value.parse::<usize>() {
+                    Ok(0) => usize::MAX,
+                    Ok(jobs) => jobs,
+                    Err(_) => return Err(format!("{value}: invalid number of jobs")),
+                };
+            }
+            "--joblog" => options.joblog = Some(value()?),
+            "--halt" => {
+                let value = value()?;
+                options.halt = match value.as_str() {
+                    "never" | "0" => Halt::Never,
+                    "soon" | "soon,fail=1" | "1" => Halt::Soon,
+                    "now" | "now,fail=1" | "2" => Halt::Now,
+                    _ => return Err(format!("{value}: invalid --halt condition")),
+                };
+            }
+            _ => return Err(format!("{name}: invalid option")),
+        }
+    }
+
+    let rest = words.as_slice();
+    match rest.iter().position(|word| word == ":::") {
+        Some(split) => {
+            options.command = rest[..split].to_vec();
+            options.arguments = Some(rest[split + 1..].to_vec());
+        }
+        None => options.command = rest.to_vec(),
+    }
+    Ok(options)
+}
+
+/// The words of the command run for `arg`, the job numbered `seq`. Without
+/// a command the argument itself is the command line.
+fn command_words(command: &[String], arg: &str, seq: usize) -> Vec<String> {
+    if command.is_empty() {
+        return arg.split_whitespace().map(str::to_string).collect();
+    }
+    let mut replaced = false;
+    let mut words: Vec<String> = command
+        .iter()
+        .map(|word| {
+            let mut out = String::with_capacity(word.len());
+            let mut rest = word.as_str();
+            while let Some(start) = rest.find('{') {
+                out.push_str(&rest[..start]);
+                rest = &rest[start..];
+                match REPLACEMENTS.iter().find(|r| rest.starts_with(**r)) {
+                    Some(replacement) => {
+                        out.push_str(&replacement_value(replacement, arg, seq));
+                        rest = &rest[replacement.len()..];
+                        replaced = true;
+                    }
+                    None => {
+                        out.push('{');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            out.push_str(rest);
+            out
+        })
+        .collect();
+    if !replaced {
+        words.push(arg.to_string());
+    }
+    words
+}
+
+fn replacement_value(replacement: &str, arg: &str, seq: usize) -> String {
+    let (dir, base) = match arg.rfind('/') {
+        Some(0) => ("/", &arg[1..]),
+        Some(slash) => (&arg[..slash], &arg[slash + 1..]),
+        None => (".", arg),
+    };
+    let strip_extension = |path: &'_ str| -> String {
+        let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+        match path[name_start..].rfind('.') {
+            Some(dot) if dot > 0 => path[..name_start + dot].to_string(),
+            _ => path.to_string(),
+        }
+    };
+    match replacement {
+        "{.}" => strip_extension(arg),
+        "{/}" => base.to_string(),
+        "{//}" => dir.to_string(),
+        "{/.}" => strip_extension(base),
+        "{#}" => seq.to_string(),
+        _ => arg.to_string(),
+    }
+}
+
+/// Start `words` as a background job whose output is collected by two
+/// threads until the job closes it
+fn start_job(context: &ShellContext, words: Vec<String>, seq: usize) -> ShellResult<Running> {
+    let line = words.join(" ");
+    let (stdout, stdout_writer) = nxsh_hal::pipe::stream_pipe()?;
+    let (stderr, stderr_writer) = nxsh_hal::pipe::stream_pipe()?;
+    let mut command = Executor::external_command(&words[0], &words[1..], context);
+    command.stdout(stdout_writer).stderr(stderr_writer);
+
+    let started = SystemTime::now();
+    let job_id = lock_jobs(&context.job_manager())?
+        .spawn_background_pipeline(line.clone(), vec![command])?;
+    Ok(Running {
+        seq,
+        job_id,
+        line,
+        started,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+fn collect(mut pipe: File) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}
+
+/// Terminate the jobs still running; one may finish before the signal
+/// arrives, which is not an error
+fn terminate(context: &ShellContext, running: &[Running]) -> ShellResult<()> {
+    let job_manager = context.job_manager();
+    let manager = lock_jobs(&job_manager)?;
+    for job in running {
+        let _ = manager.send_signal_to_job(job.job_id, JobSignal::Terminate);
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! renice built-in command implementation
+//!
+//! renice changes the nice value of running processes, named by pid or by
+//! job spec, and reports the old and new value of each as util-linux does.
+
+use super::lock_jobs;
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::{Builtin, ExecutionResult};
+use nxsh_hal::process::{priority, set_priority};
+use nxsh_hal::HalError;
+
+pub struct ReniceBuiltin;
+
+impl Builtin for ReniceBuiltin {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let usage = |message: &str| {
+            Ok(ExecutionResult::failure(1).with_error(
+                format!(
+                    "renice: {message}\n\
+                     renice: usage: renice [-n] priority [--relative] [-p] pid|%job ...\n"
+                )
+                .into_bytes(),
+            ))
+        };
+
+        let mut value = None;
+        let mut relative = false;
+        let mut operands = Vec::new();
+        let mut words = args.iter();
+        while let Some(arg) = words.next() {
+            match arg.as_str() {
+                "-n" | "--priority" => match words.next() {
+                    Some(spec) => value = Some(spec.as_str()),
+                    None => return usage(&format!("{arg}: option requires an argument")),
+                },
+                "--relative" => relative = true,
+                "-p" | "--pid" | "--" => {}
+                // The priority comes first, and may be negative
+                _ if value.is_none() => value = Some(arg.as_str()),
+                _ if arg.starts_with('-') => return usage(&format!("{arg}: invalid option")),
+                _ => operands.push(arg.as_str()),
+            }
+        }
+        let Some(value) = value else {
+            return usage("missing priority");
+        };
+        let Ok(value) = value.parse::<i32>() else {
+            return usage(&format!("{value}: invalid priority"));
+        };
+        if operands.is_empty() {
+            return usage("missing pid");
+        }
+
+        let job_manager = context.job_manager();
+        let mut output = String::new();
+        let mut errors = String::new();
+        for operand in operands {
+            // A job spec stands for every process of the job
+            let pids: Vec<u32> = if operand.starts_with('%') {
+                let manager = lock_jobs(&job_manager)?;
+                let job = match manager.resolve_job_spec(operand) {
+                    Ok(job_id) => manager.get_job(job_id)?,
+                    Err(e) => {
+                        errors.push_str(&format!("renice: {}\n", e.message));
+                        continue;
+                    }
+                };
+                job.map(|job| job.processes.iter().map(|p| p.pid).collect())
+                    .unwrap_or_default()
+            } else {
+                match operand.parse() {
+                    Ok(pid) => vec![pid],
+                    Err(_) => {
+                        errors.push_str(&format!("renice: {operand}: invalid pid\n"));
+                        continue;
+                    }
+                }
+            };
+            for pid in pids.into_iter().filter(|&pid| pid != 0) {
+                let changed = priority(pid).and_then(|old| {
+                    let new = if relative {
+                        old.saturating_add(value)
+                    } else {
+                        value
+                    };
+                    set_priority(pid, new)?;
+                    Ok((old, priority(pid)?))
+                });
+                match changed {
+                    Ok((old, new)) => output.push_str(&format!(
+                        "{pid} (process ID) old priority {old}, new priority {new}\n"
+                    )),
+                    Err(e) => {
+                        let reason = match e {
+                            HalError::Process(process) => process.message,
+                            other => other.to_string(),
+                        };
+                        errors.push_str(&format!(
+                            "renice: failed to set priority for {pid} (process ID): {reason}\n"
+                        ))
+                    }
+                }
+            }
+        }
+
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        Ok(ExecutionResult::success(exit_code)
+            .with_output(output.into_bytes())
+            .with_error(errors.into_bytes()))
+    }
+
+    fn name(&self) -> &'static str {
+        "renice"
+    }
+
+    fn help(&self) -> &'static str {
+        "Change the priority of running processes"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "renice [-n] priority [--relative] [-p] pid|%job ..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Set the nice value of each given process, or of every process of a job.\n\
+        Nice values run from -20, scheduled most favourably, to 19; lowering\n\
+        one usually needs privileges. Each change is reported with the old and\n\
+        new value, and the status is 1 if any process could not be changed.\n\n\
+        Options:\n\
+        -n priority  The nice value to set\n\
+        --relative   Add priority to each process's current value instead\n\
+        -p           Take the operands as pids (the default)"
+    }
+
+    fn usage(&self) -> &'static str {
+        "renice [-n] priority [--relative] [-p] pid|%job ...\n\n\
+        Examples:\n\
+        renice 10 -p 1234        # Set process 1234 to nice value 10\n\
+        renice -n 5 --relative %1   # Lower job 1's priority by 5"
+    }
+}
@@ -0,0 +1,221 @@
+//! wait built-in command implementation
+//!
+//! The wait command blocks until background jobs finish and returns the exit
+//! status of the last one waited for.
+
+use super::lock_jobs;
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::{Builtin, ExecutionResult};
+use crate::job::{Job, JobId, JobManager};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How often a waiting shell re-checks the job table
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct WaitBuiltin;
+
+impl Builtin for WaitBuiltin {
+    fn execute(&self, context: &mut ShellContext, args: &[String]) -> ShellResult<ExecutionResult> {
+        let job_manager = context.job_manager();
+
+        let mut next = false;
+        let mut pid_var = None;
+        let mut operands = Vec::new();
+        let mut words = args.iter();
+        while let Some(arg) = words.next() {
+            match arg.as_str() {
+                "-n" => next = true,
+                "-p" => match words.next() {
+                    Some(name) => pid_var = Some(name.clone()),
+                    None => {
+                        return Ok(ExecutionResult::failure(2)
+                            .with_error(b"wait: -p: option requires an argument\n".to_vec()))
+                    }
+                },
+                "--" => {}
+                _ => operands.push(arg.as_str()),
+            }
+        }
+
+        let mut resolved = Vec::new();
+        for operand in &operands {
+            resolved.push(resolve_operand(&job_manager, operand)?);
+        }
+
+        let mut errors = String::new();
+        if next {
+            let mut job_ids = Vec::new();
+            for operand in resolved {
+                match operand {
+                    Ok(job_id) => job_ids.push(job_id),
+                    Err(message) => errors.push_str(&message),
+                }
+            }
+            // With operands only those jobs count; none resolving is an error
+            if !operands.is_empty() && job_ids.is_empty() {
+                return Ok(ExecutionResult::success(127).with_error(errors.into_bytes()));
+            }
+            let (exit_code, job) = wait_any(context, &job_manager, &job_ids)?;
+            if let (Some(name), Some(job)) = (&pid_var, &job) {
+                context.set_var(name.clone(), job_pid(job));
+            }
+            return Ok(ExecutionResult::success(exit_code).with_error(errors.into_bytes()));
+        }
+
+        if operands.is_empty() {
+            // Plain `wait` reaps every job and always succeeds
+            let ids: Vec<JobId> = lock_jobs(&job_manager)?
+                .get_all_jobs()
+                .into_iter()
+                .map(|job| job.id)
+                .collect();
+            for job_id in ids {
+                wait_job(context, &job_manager, job_id)?;
+            }
+            return Ok(ExecutionResult::success(0));
+        }
+
+        let mut exit_code = 0;
+        for operand in resolved {
+            match operand {
+                Ok(job_id) => {
+                    let (code, job) = wait_job(context, &job_manager, job_id)?;
+                    exit_code = code;
+                    if let (Some(name), Some(job)) = (&pid_var, &job) {
+                        context.set_var(name.clone(), job_pid(job));
+                    }
+                }
+                Err(message) => {
+                    errors.push_str(&message);
+                    exit_code = 127;
+                }
+            }
+        }
+
+        Ok(ExecutionResult::success(exit_code).with_error(errors.into_bytes()))
+    }
+
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn help(&self) -> &'static str {
+        "Wait for jobs to finish"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "wait [-n] [-p var] [job_spec | pid ...]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Wait for each given job or process and return the exit status of the last one.\n\
+        With no operands, wait for all background jobs and return 0.\n\n\
+        Options:\n\
+        -n      Wait for the next job to finish and return its status; with\n\
+        \x20       operands, the next of those jobs\n\
+        -p var  Assign the process ID of the job waited for to var"
+    }
+
+    fn usage(&self) -> &'static str {
+        "wait [-n] [-p var] [%n | pid ...]\n\n\
+        Examples:\n\
+        wait        # Wait for every background job\n\
+        wait %1     # Wait for job 1 and return its status\n\
+        wait $!     # Wait for the most recent background job\n\
+        wait -n -p pid %1 %2   # Wait for job 1 or 2; $pid is its pid"
+    }
+}
+
+/// Block until `job_id` finishes and return its exit status along with the
+/// job. The job leaves the table once waited for, so it is not reported as
+/// done later.
+fn wait_job(
+    context: &ShellContext,
+    job_manager: &Mutex<JobManager>,
+    job_id: JobId,
+) -> ShellResult<(i32, Option<Job>)> {
+    loop {
+        {
+            let mut manager = lock_jobs(job_manager)?;
+            match manager.get_job(job_id)? {
+                Some(job) if job.is_finished() => {
+                    manager.remove_job(job_id);
+                    return Ok((job.exit_code().unwrap_or(0), Some(job)));
+                }
+                Some(_) => {}
+                None => return Ok((127, None)),
+            }
+        }
+        if context.is_timed_out() {
+            return Ok((124, None));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Block until any job finishes (`wait -n`), or any of `among` when it is
+/// not empty, and return its exit status along with the job. The status is
+/// 127 when there are no jobs to wait for.
+fn wait_any(
+    context: &ShellContext,
+    job_manager: &Mutex<JobManager>,
+    among: &[JobId],
+) -> ShellResult<(i32, Option<Job>)> {
+    loop {
+        {
+            let mut manager = lock_jobs(job_manager)?;
+            let jobs: Vec<Job> = manager
+                .get_all_jobs()
+                .into_iter()
+                .filter(|job| among.is_empty() || among.contains(&job.id))
+                .collect();
+            if jobs.is_empty() {
+                return Ok((127, None));
+            }
+            if let Some(job) = jobs
+                .into_iter()
+                .filter(|job| job.is_finished())
+                .min_by_key(|job| job.id)
+            {
+                manager.remove_job(job.id);
+                return Ok((job.exit_code().unwrap_or(0), Some(job)));
+            }
+        }
+        if context.is_timed_out() {
+            return Ok((124, None));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Find the job a `%spec` or pid operand names, or the message to report
+fn resolve_operand(
+    job_manager: &Mutex<JobManager>,
+    operand: &str,
+) -> ShellResult<Result<JobId, String>> {
+    let manager = lock_jobs(job_manager)?;
+    Ok(if operand.starts_with('%') {
+        manager
+            .resolve_job_spec(operand)
+            .map_err(|e| format!("wait: {}\n", e.message))
+    } else {
+        operand
+            .parse()
+            .ok()
+            .and_then(|pid| manager.job_for_pid(pid))
+            .ok_or_else(|| format!("wait: pid {operand} is not a child of this shell\n"))
+    })
+}
+
+/// The pid `$!` gave for a job: its last process that started
+fn job_pid(job: &Job) -> String {
+    job.processes
+        .iter()
+        .rev()
+        .map(|process| process.pid)
+        .find(|&pid| pid != 0)
+        .map_or_else(String::new, |pid| pid.to_string())
+}
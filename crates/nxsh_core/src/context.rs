@@ -4,11 +4,14 @@
 //! managing variables, functions, aliases, and execution state.
 
 use crate::error::{ErrorKind, ShellError, ShellResult};
+use crate::executor::PostCommandHooks;
+use crate::hooks::HookRegistry;
 use crate::job::{JobId, JobManager};
+use crate::plugin_commands::PluginCommandRegistry;
 use crate::stream::Stream;
 use std::io::IsTerminal;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     io,
     path::PathBuf,
     sync::{Arc, Mutex, RwLock},
@@ -127,6 +130,17 @@ fn get_parent_pid() -> u32 {
     0
 }
 
+/// Split `name[subscript]` into its parts; `None` for plain names
+pub fn split_subscript(key: &str) -> Option<(&str, &str)> {
+    let inner = key.strip_suffix(']')?;
+    let open = inner.find('[')?;
+    let name = &inner[..open];
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| (name, &inner[open + 1..]))
+}
+
 /// Detect if this is a login shell
 fn detect_login_shell() -> bool {
     // Ultra-early decisive signals to avoid races with concurrent env mutations in tests
@@ -534,6 +548,13 @@ pub struct ShellContext {
     pub options: Arc<RwLock<ShellOptions>>,
     /// Active jobs in this context
     pub jobs: Arc<RwLock<HashMap<u32, crate::job::Job>>>,
+    /// Process ID of the most recent background job (`$!`)
+    pub last_background_pid: Option<u32>,
+    /// Remembered locations of external commands (`hash`)
+    pub command_hash: Arc<Mutex<nxsh_hal::command::PathCache>>,
+    /// Controlling terminal, when the shell runs job control on it. Not
+    /// inherited by subcontexts, whose job tables are discarded.
+    pub terminal: Option<Arc<nxsh_hal::TerminalControl>>,
     /// Shell level (for nested shells)
     pub shell_level: u32,
     /// Initialization time
@@ -556,6 +577,22 @@ pub struct ShellContext {
     temp_id_counter: Arc<Mutex<u64>>,
     /// Macro system (optional lazy init)
     pub macro_system: Arc<RwLock<crate::macros::MacroSystem>>,
+    /// Registered traps (signals, EXIT, ERR, DEBUG)
+    pub traps: Arc<RwLock<crate::trap::TrapTable>>,
+    /// `$0` and the positional parameters `$1..$N`
+    pub positional: Arc<RwLock<PositionalParams>>,
+    /// Indexed and associative array variables
+    pub arrays: Arc<RwLock<HashMap<String, ShellArray>>>,
+    /// Active shell function calls, innermost last
+    pub call_stack: Arc<RwLock<Vec<CallFrame>>>,
+    /// Told about every command line the executor finishes. Not inherited
+    /// by subcontexts, so only top-level commands are reported.
+    pub post_command_hooks: PostCommandHooks,
+    /// Commands of loaded plugins, shared with subcontexts
+    pub plugin_commands: PluginCommandRegistry,
+    /// Run on shell events. Not inherited by subcontexts, so a hook added
+    /// in a subshell is gone when it exits.
+    pub hooks: HookRegistry,
 }
 
 impl std::fmt::Debug for ShellContext {
@@ -581,16 +618,117 @@ impl std::fmt::Debug for ShellContext {
             )
             .field("options", &"Arc<RwLock<ShellOptions>>")
             .field("jobs", &"Arc<RwLock<HashMap<u32, Job>>>")
+            .field("last_background_pid", &self.last_background_pid)
+            .field("command_hash", &"Arc<Mutex<PathCache>>")
+            .field("terminal", &self.terminal.is_some())
             .field("shell_level", &self.shell_level)
             .field("init_time", &self.init_time)
             .field("history", &"Arc<Mutex<Vec<String>>>")
             .field("dir_stack", &"Arc<Mutex<Vec<PathBuf>>>")
+            .field("traps", &"Arc<RwLock<TrapTable>>")
+            .field("positional", &"Arc<RwLock<PositionalParams>>")
+            .field("arrays", &"Arc<RwLock<HashMap<String, ShellArray>>>")
+            .field("call_stack", &"Arc<RwLock<Vec<CallFrame>>>")
+            .field("post_command_hooks", &self.post_command_hooks)
+            .field("plugin_commands", &self.plugin_commands)
+            .field("hooks", &self.hooks)
             .field("interactive", &self.interactive)
             .field("login_shell", &self.login_shell)
             .finish()
     }
 }
 
+/// Script name (`$0`) and positional parameters (`$1..$N`)
+#[derive(Debug, Clone)]
+pub struct PositionalParams {
+    /// Value of `$0`: the script path, or the shell name when interactive
+    pub name: String,
+    /// `$1..$N`
+    pub args: Vec<String>,
+}
+
+impl PositionalParams {
+    pub fn new(name: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            args,
+        }
+    }
+}
+
+impl Default for PositionalParams {
+    fn default() -> Self {
+        Self::new("nxsh", Vec::new())
+    }
+}
+
+/// Value of an array variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellArray {
+    /// Sparse array indexed by non-negative integers (`a=(x y)`, `a[7]=z`)
+    Indexed(BTreeMap<usize, String>),
+    /// Array keyed by strings, created with `declare -A`
+    Associative(BTreeMap<String, String>),
+}
+
+impl ShellArray {
+    /// Indexed array holding `values` at `0..N`
+    pub fn from_values(values: impl IntoIterator<Item = String>) -> Self {
+        ShellArray::Indexed(values.into_iter().enumerate().collect())
+    }
+
+    pub fn new_associative() -> Self {
+        ShellArray::Associative(BTreeMap::new())
+    }
+
+    pub fn is_associative(&self) -> bool {
+        matches!(self, ShellArray::Associative(_))
+    }
+
+    /// Number of set elements (`${#a[@]}`)
+    pub fn len(&self) -> usize {
+        match self {
+            ShellArray::Indexed(map) => map.len(),
+            ShellArray::Associative(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Element values in index (or key) order (`${a[@]}`)
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            ShellArray::Indexed(map) => map.values().cloned().collect(),
+            ShellArray::Associative(map) => map.values().cloned().collect(),
+        }
+    }
+
+    /// Set indices or keys (`${!a[@]}`)
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            ShellArray::Indexed(map) => map.keys().map(|k| k.to_string()).collect(),
+            ShellArray::Associative(map) => map.keys().cloned().collect(),
+        }
+    }
+
+    /// Append after the highest set index; associative arrays use the
+    /// next free numeric key
+    pub fn push(&mut self, value: String) {
+        match self {
+            ShellArray::Indexed(map) => {
+                let next = map.keys().next_back().map_or(0, |k| k + 1);
+                map.insert(next, value);
+            }
+            ShellArray::Associative(map) => {
+                let next = (0..).find(|i: &usize| !map.contains_key(&i.to_string()));
+                map.insert(next.unwrap_or(0).to_string(), value);
+            }
+        }
+    }
+}
+
 /// Shell variable with metadata
 #[derive(Debug, Clone)]
 pub struct ShellVariable {
@@ -626,6 +764,23 @@ impl ShellVariable {
     }
 }
 
+/// One active shell function call
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// Name of the function, as listed in `FUNCNAME`
+    pub function: String,
+    /// Variables made `local` in this call, with the values they shadow
+    locals: Vec<(String, ShadowedVar)>,
+}
+
+/// Everything a `local` declaration hides until its function returns
+#[derive(Debug, Clone)]
+struct ShadowedVar {
+    var: Option<ShellVariable>,
+    env: Option<String>,
+    array: Option<ShellArray>,
+}
+
 /// Shell configuration options
 #[derive(Debug, Clone)]
 pub struct ShellOptions {
@@ -657,8 +812,13 @@ pub struct ShellOptions {
     pub completion: bool,
     /// Spell checking for directory names
     pub cdspell: bool,
+    /// Offer to run the closest match for a mistyped command
+    pub correct: bool,
     /// Check window size after each command
     pub checkwinsize: bool,
+    /// Run foreground programs on a pseudo-terminal when the shell has no
+    /// terminal to hand them
+    pub pty: bool,
     /// Enable extended globbing
     pub extglob: bool,
     /// Enable null globbing (empty expansion for no matches)
@@ -673,8 +833,6 @@ pub struct ShellOptions {
     pub continue_requested: bool,
     /// Continue execution on errors
     pub continue_on_error: bool,
-    /// Enable process isolation for subshells
-    pub enable_process_isolation: bool,
     /// Current subshell nesting level
     pub subshell_level: u32,
 }
@@ -696,7 +854,9 @@ impl Default for ShellOptions {
             histexpand: true,
             completion: true,
             cdspell: false,
+            correct: false,
             checkwinsize: true,
+            pty: false,
             extglob: false,
             nullglob: false,
             nocaseglob: false,
@@ -704,12 +864,116 @@ impl Default for ShellOptions {
             break_requested: false,
             continue_requested: false,
             continue_on_error: false,
-            enable_process_isolation: true,
             subshell_level: 0,
         }
     }
 }
 
+impl ShellOptions {
+    /// Option names understood by `set -o`, in listing order
+    pub const SET_OPTIONS: &'static [&'static str] = &[
+        "emacs",
+        "errexit",
+        "hashall",
+        "histexpand",
+        "monitor",
+        "noclobber",
+        "noglob",
+        "nounset",
+        "pipefail",
+        "verbose",
+        "vi",
+        "xtrace",
+    ];
+
+    /// Option names understood by `shopt`, in listing order
+    pub const SHOPT_OPTIONS: &'static [&'static str] = &[
+        "cdspell",
+        "checkwinsize",
+        "correct",
+        "dotglob",
+        "extglob",
+        "nocaseglob",
+        "nullglob",
+        "pty",
+    ];
+
+    /// Set an option by long name or `set` flag letter; false if unknown
+    pub fn set(&mut self, option: &str, value: bool) -> bool {
+        match option {
+            "errexit" | "e" => self.errexit = value,
+            "xtrace" | "x" => self.xtrace = value,
+            "pipefail" => self.pipefail = value,
+            "noclobber" | "C" => self.noclobber = value,
+            "noglob" | "f" => self.noglob = value,
+            "hashall" | "h" => self.hashall = value,
+            "monitor" | "m" => self.monitor = value,
+            "nounset" | "u" => self.nounset = value,
+            "verbose" | "v" => self.verbose = value,
+            "vi" => {
+                self.vi_mode = value;
+                if value {
+                    self.emacs_mode = false;
+                }
+            }
+            "emacs" => {
+                self.emacs_mode = value;
+                if value {
+                    self.vi_mode = false;
+                }
+            }
+            "histexpand" | "H" => self.histexpand = value,
+            "completion" => self.completion = value,
+            "cdspell" => self.cdspell = value,
+            "correct" => self.correct = value,
+            "checkwinsize" => self.checkwinsize = value,
+            "pty" => self.pty = value,
+            "extglob" => self.extglob = value,
+            "nullglob" => self.nullglob = value,
+            "nocaseglob" => self.nocaseglob = value,
+            "dotglob" => self.dotglob = value,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The `set` flag letters of the options that are on, as `$-` shows them
+    pub fn flags(&self) -> String {
+        ["e", "f", "h", "m", "u", "v", "x", "C", "H"]
+            .into_iter()
+            .filter(|flag| self.get(flag) == Some(true))
+            .collect()
+    }
+
+    /// Read an option by long name or `set` flag letter
+    pub fn get(&self, option: &str) -> Option<bool> {
+        Some(match option {
+            "errexit" | "e" => self.errexit,
+            "xtrace" | "x" => self.xtrace,
+            "pipefail" => self.pipefail,
+            "noclobber" | "C" => self.noclobber,
+            "noglob" | "f" => self.noglob,
+            "hashall" | "h" => self.hashall,
+            "monitor" | "m" => self.monitor,
+            "nounset" | "u" => self.nounset,
+            "verbose" | "v" => self.verbose,
+            "vi" => self.vi_mode,
+            "emacs" => self.emacs_mode,
+            "histexpand" | "H" => self.histexpand,
+            "completion" => self.completion,
+            "cdspell" => self.cdspell,
+            "correct" => self.correct,
+            "checkwinsize" => self.checkwinsize,
+            "pty" => self.pty,
+            "extglob" => self.extglob,
+            "nullglob" => self.nullglob,
+            "nocaseglob" => self.nocaseglob,
+            "dotglob" => self.dotglob,
+            _ => return None,
+        })
+    }
+}
+
 impl ShellContext {
     /// Create a comprehensive shell context with full functionality
     /// COMPLETE initialization with ALL system integration as required
@@ -746,6 +1010,13 @@ impl ShellContext {
             stdout_capture: None,
             options: Arc::new(RwLock::new(ShellOptions::default())),
             jobs: Arc::new(RwLock::new(HashMap::new())),
+            last_background_pid: None,
+            command_hash: Arc::new(Mutex::new(nxsh_hal::command::PathCache::new())),
+            call_stack: Arc::new(RwLock::new(Vec::new())),
+            post_command_hooks: PostCommandHooks::default(),
+            plugin_commands: PluginCommandRegistry::default(),
+            hooks: HookRegistry::default(),
+            terminal: None,
             shell_level,
             init_time: Instant::now(),
             history: Arc::new(Mutex::new(Vec::new())),
@@ -773,6 +1044,9 @@ impl ShellContext {
                 .map(Duration::from_millis),
             temp_id_counter: Arc::new(Mutex::new(0)),
             macro_system: Arc::new(RwLock::new(crate::macros::MacroSystem::new())),
+            traps: Arc::new(RwLock::new(crate::trap::TrapTable::new())),
+            positional: Arc::new(RwLock::new(PositionalParams::default())),
+            arrays: Arc::new(RwLock::new(HashMap::new())),
         }
         // Post-construction adjustment: if global timeout set, prefer continue_on_error=true
         // so timeouts surface as 124 even with intermediate failures.
@@ -833,6 +1107,13 @@ impl ShellContext {
             stdout_capture: None,
             options: Arc::new(RwLock::new(ShellOptions::default())),
             jobs: Arc::new(RwLock::new(HashMap::new())),
+            last_background_pid: None,
+            command_hash: Arc::new(Mutex::new(nxsh_hal::command::PathCache::new())),
+            call_stack: Arc::new(RwLock::new(Vec::new())),
+            post_command_hooks: PostCommandHooks::default(),
+            plugin_commands: PluginCommandRegistry::default(),
+            hooks: HookRegistry::default(),
+            terminal: None,
             shell_level,
             init_time: Instant::now(),
             history: Arc::new(Mutex::new(Vec::new())),
@@ -860,6 +1141,9 @@ impl ShellContext {
                 .map(Duration::from_millis),
             temp_id_counter: Arc::new(Mutex::new(0)),
             macro_system: Arc::new(RwLock::new(crate::macros::MacroSystem::new())),
+            traps: Arc::new(RwLock::new(crate::trap::TrapTable::new())),
+            positional: Arc::new(RwLock::new(PositionalParams::default())),
+            arrays: Arc::new(RwLock::new(HashMap::new())),
         };
 
         // When a global timeout is configured, prefer continuing on intermediate errors
@@ -957,6 +1241,17 @@ impl ShellContext {
         self.global_deadline = None;
     }
 
+    /// Instant the global execution deadline falls on, if one is configured
+    pub fn global_deadline(&self) -> Option<Instant> {
+        self.global_deadline
+    }
+
+    /// Replace the global execution deadline. `timeout` moves it closer for
+    /// the command it runs and puts the old one back afterwards.
+    pub fn set_global_deadline(&mut self, deadline: Option<Instant>) {
+        self.global_deadline = deadline;
+    }
+
     /// If no global deadline is configured but NXSH_TIMEOUT_MS is present in the environment,
     /// initialize the deadline from the environment value. This is a safe, idempotent helper
     /// to satisfy scenarios where the environment is set after process init but before execution.
@@ -1004,8 +1299,28 @@ impl ShellContext {
         self.per_command_timeout
     }
 
+    /// Full path of an external command, looked up in `$PATH` through the
+    /// `hash` table. Names with a slash are used as they are.
+    pub fn resolve_command(&self, name: &str) -> Option<PathBuf> {
+        if name.contains('/') {
+            return Some(PathBuf::from(name));
+        }
+        let path = self.get_var("PATH").unwrap_or_default();
+        self.command_hash
+            .lock()
+            .ok()?
+            .resolve(name, std::ffi::OsStr::new(&path))
+    }
+
     /// Get environment variable
     pub fn get_var(&self, key: &str) -> Option<String> {
+        if let Some(special) = self.special_param(key) {
+            return special;
+        }
+        if let Some((name, subscript)) = split_subscript(key) {
+            return self.get_array_element(name, subscript);
+        }
+
         // Check shell variables first
         if let Ok(vars) = self.vars.read() {
             if let Some(var) = vars.get(key) {
@@ -1015,10 +1330,16 @@ impl ShellContext {
 
         // Then check environment variables
         if let Ok(env) = self.env.read() {
-            env.get(key).cloned()
-        } else {
-            None
+            if let Some(value) = env.get(key) {
+                return Some(value.clone());
+            }
         }
+
+        // `$a` on an array means `${a[0]}`
+        if self.has_array(key) {
+            return self.get_array_element(key, "0");
+        }
+        None
     }
 
     /// Set environment variable
@@ -1041,6 +1362,394 @@ impl ShellContext {
         }
     }
 
+    /// Remove a variable from both the shell and the environment.
+    /// `name[sub]` removes a single array element.
+    pub fn unset_var(&self, key: &str) -> bool {
+        if let Some((name, subscript)) = split_subscript(key) {
+            return self.unset_array_element(name, subscript);
+        }
+        let in_array = self
+            .arrays
+            .write()
+            .map(|mut arrays| arrays.remove(key).is_some())
+            .unwrap_or(false);
+        let in_env = self
+            .env
+            .write()
+            .map(|mut env| env.remove(key).is_some())
+            .unwrap_or(false);
+        let in_vars = self
+            .vars
+            .write()
+            .map(|mut vars| vars.remove(key).is_some())
+            .unwrap_or(false);
+        in_env || in_vars || in_array
+    }
+
+    /// Whether `name` is an array variable
+    pub fn has_array(&self, name: &str) -> bool {
+        self.arrays
+            .read()
+            .map(|arrays| arrays.contains_key(name))
+            .unwrap_or(false)
+    }
+
+    /// Snapshot of the array `name`
+    pub fn get_array(&self, name: &str) -> Option<ShellArray> {
+        self.arrays.read().ok()?.get(name).cloned()
+    }
+
+    /// Replace `name` with `array`, dropping any scalar of the same name
+    pub fn set_array(&self, name: impl Into<String>, array: ShellArray) {
+        let name = name.into();
+        if let Ok(mut env) = self.env.write() {
+            env.remove(&name);
+        }
+        if let Ok(mut vars) = self.vars.write() {
+            vars.remove(&name);
+        }
+        if let Ok(mut arrays) = self.arrays.write() {
+            arrays.insert(name, array);
+        }
+    }
+
+    /// `${name[subscript]}`. `@` and `*` join every element with a space
+    /// (or the first IFS character for `*`).
+    pub fn get_array_element(&self, name: &str, subscript: &str) -> Option<String> {
+        // Work on a snapshot: resolving a subscript may read other variables
+        let array = self.get_array(name);
+        if subscript == "@" || subscript == "*" {
+            let values = match array {
+                Some(array) => array.values(),
+                None => self.get_var(name).into_iter().collect(),
+            };
+            let sep = if subscript == "*" {
+                self.get_var("IFS")
+                    .map(|ifs| ifs.chars().next().map(String::from).unwrap_or_default())
+                    .unwrap_or_else(|| " ".to_string())
+            } else {
+                " ".to_string()
+            };
+            return Some(values.join(&sep));
+        }
+        match array {
+            Some(ShellArray::Associative(map)) => map.get(&self.subscript_key(subscript)).cloned(),
+            Some(ShellArray::Indexed(map)) => {
+                let index = self.subscript_index(subscript, map.keys().next_back().copied())?;
+                map.get(&index).cloned()
+            }
+            None => {
+                // A scalar behaves like a one-element array
+                let index = self.subscript_index(subscript, Some(0))?;
+                (index == 0).then(|| self.get_var(name)).flatten()
+            }
+        }
+    }
+
+    /// `name[subscript]=value`. Creates an indexed array when `name` is
+    /// unset; an existing scalar becomes element 0.
+    pub fn set_array_element(
+        &self,
+        name: &str,
+        subscript: &str,
+        value: impl Into<String>,
+    ) -> ShellResult<()> {
+        let mut array = match self.get_array(name) {
+            Some(array) => array,
+            None => ShellArray::from_values(self.get_var(name)),
+        };
+        match &mut array {
+            ShellArray::Associative(map) => {
+                map.insert(self.subscript_key(subscript), value.into());
+            }
+            ShellArray::Indexed(map) => {
+                let last = map.keys().next_back().copied();
+                let index = self.subscript_index(subscript, last).ok_or_else(|| {
+                    ShellError::new(
+                        ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                        format!("{name}[{subscript}]: bad array subscript"),
+                    )
+                })?;
+                map.insert(index, value.into());
+            }
+        }
+        self.set_array(name, array);
+        Ok(())
+    }
+
+    /// Append `value` after the last element of `name`, creating it if needed
+    pub fn push_array_element(&self, name: &str, value: impl Into<String>) {
+        let mut array = match self.get_array(name) {
+            Some(array) => array,
+            None => ShellArray::from_values(self.get_var(name)),
+        };
+        array.push(value.into());
+        self.set_array(name, array);
+    }
+
+    fn unset_array_element(&self, name: &str, subscript: &str) -> bool {
+        if subscript == "@" || subscript == "*" {
+            return self.unset_var(name);
+        }
+        let Some(mut array) = self.get_array(name) else {
+            return false;
+        };
+        let removed = match &mut array {
+            ShellArray::Associative(map) => map.remove(&self.subscript_key(subscript)).is_some(),
+            ShellArray::Indexed(map) => {
+                let last = map.keys().next_back().copied();
+                self.subscript_index(subscript, last)
+                    .is_some_and(|index| map.remove(&index).is_some())
+            }
+        };
+        if removed {
+            if let Ok(mut arrays) = self.arrays.write() {
+                arrays.insert(name.to_string(), array);
+            }
+        }
+        removed
+    }
+
+    /// Indexed subscripts are arithmetic expressions; negative values count
+    /// back from `last + 1`
+    fn subscript_index(&self, subscript: &str, last: Option<usize>) -> Option<usize> {
+        let value = crate::arithmetic::evaluate(subscript, self).ok()?;
+        if value >= 0 {
+            return usize::try_from(value).ok();
+        }
+        let len = last.map_or(0, |l| l as i64 + 1);
+        usize::try_from(len + value).ok()
+    }
+
+    /// Associative keys are taken literally after quote removal and
+    /// `$name` / `${name}` expansion
+    fn subscript_key(&self, subscript: &str) -> String {
+        let unquoted = subscript
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(subscript);
+        if let Some(literal) = subscript
+            .strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+        {
+            return literal.to_string();
+        }
+        let mut key = String::with_capacity(unquoted.len());
+        let mut rest = unquoted;
+        while let Some(pos) = rest.find('$') {
+            key.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let (name, tail) = if let Some(inner) = after.strip_prefix('{') {
+                match inner.find('}') {
+                    Some(end) => (&inner[..end], &inner[end + 1..]),
+                    None => ("", after),
+                }
+            } else {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            };
+            if name.is_empty() {
+                key.push('$');
+            } else {
+                key.push_str(&self.get_var(name).unwrap_or_default());
+            }
+            rest = tail;
+        }
+        key.push_str(rest);
+        key
+    }
+
+    /// Resolve special parameters (`$0`, `$1..$N`, `$#`, `$@`, `$*`, `$?`, `$$`, `$!`, `$-`).
+    /// Returns `None` when `key` is an ordinary variable name.
+    fn special_param(&self, key: &str) -> Option<Option<String>> {
+        let value = match key {
+            "?" => Some(self.get_exit_status().to_string()),
+            "$" => Some(std::process::id().to_string()),
+            "!" => self.last_background_pid.map(|pid| pid.to_string()),
+            "-" => {
+                let mut flags = self.options.read().map(|o| o.flags()).unwrap_or_default();
+                if self.interactive {
+                    flags.push('i');
+                }
+                Some(flags)
+            }
+            "#" => Some(self.positional_args().len().to_string()),
+            "@" => Some(self.positional_args().join(" ")),
+            "*" => {
+                // `$*` joins with the first character of IFS
+                let sep = self
+                    .get_var("IFS")
+                    .map(|ifs| ifs.chars().next().map(String::from).unwrap_or_default())
+                    .unwrap_or_else(|| " ".to_string());
+                Some(self.positional_args().join(&sep))
+            }
+            "0" => self.positional.read().ok().map(|p| p.name.clone()),
+            _ if !key.is_empty() && key.bytes().all(|b| b.is_ascii_digit()) => {
+                let index = key.parse::<usize>().ok()?.checked_sub(1)?;
+                return Some(self.positional_args().get(index).cloned());
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Current positional parameters (`$1..$N`)
+    pub fn positional_args(&self) -> Vec<String> {
+        self.positional
+            .read()
+            .map(|p| p.args.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replace the positional parameters, returning the previous ones
+    pub fn set_positional_args(&self, args: Vec<String>) -> Vec<String> {
+        match self.positional.write() {
+            Ok(mut p) => std::mem::replace(&mut p.args, args),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Drop the first `n` positional parameters; false if there are fewer than `n`
+    pub fn shift_positional(&self, n: usize) -> bool {
+        match self.positional.write() {
+            Ok(mut p) if n <= p.args.len() => {
+                p.args.drain(..n);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Enter a call of function `name`; its `local` variables live until the
+    /// matching [`pop_call_frame`](Self::pop_call_frame)
+    pub fn push_call_frame(&self, name: &str) {
+        if let Ok(mut stack) = self.call_stack.write() {
+            stack.push(CallFrame {
+                function: name.to_string(),
+                locals: Vec::new(),
+            });
+        }
+        self.refresh_funcname();
+    }
+
+    /// Leave the innermost function call, restoring every variable its
+    /// `local` declarations shadowed
+    pub fn pop_call_frame(&self) {
+        let frame = self
+            .call_stack
+            .write()
+            .ok()
+            .and_then(|mut stack| stack.pop());
+        if let Some(frame) = frame {
+            for (name, shadowed) in frame.locals.into_iter().rev() {
+                self.restore_shadowed(&name, shadowed);
+            }
+        }
+        self.refresh_funcname();
+    }
+
+    /// Whether a shell function is running
+    pub fn in_function(&self) -> bool {
+        self.call_stack
+            .read()
+            .map(|stack| !stack.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Make `name` local to the innermost function call: the current value
+    /// is put aside and the name starts out unset. Functions called from
+    /// here see the local value (dynamic scoping). False outside a function.
+    pub fn make_local(&self, name: &str) -> bool {
+        let Ok(mut stack) = self.call_stack.write() else {
+            return false;
+        };
+        let Some(frame) = stack.last_mut() else {
+            return false;
+        };
+        if frame.locals.iter().any(|(local, _)| local == name) {
+            return true;
+        }
+        let shadowed = ShadowedVar {
+            var: self
+                .vars
+                .write()
+                .ok()
+                .and_then(|mut vars| vars.remove(name)),
+            env: self.env.write().ok().and_then(|mut env| env.remove(name)),
+            array: self
+                .arrays
+                .write()
+                .ok()
+                .and_then(|mut arrays| arrays.remove(name)),
+        };
+        frame.locals.push((name.to_string(), shadowed));
+        true
+    }
+
+    /// Whether `name` was made local in the innermost function call
+    pub fn is_local(&self, name: &str) -> bool {
+        self.call_stack
+            .read()
+            .ok()
+            .and_then(|stack| {
+                stack
+                    .last()
+                    .map(|frame| frame.locals.iter().any(|(local, _)| local == name))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Names made local in the innermost function call, in declaration order
+    pub fn local_names(&self) -> Vec<String> {
+        self.call_stack
+            .read()
+            .ok()
+            .and_then(|stack| {
+                stack
+                    .last()
+                    .map(|frame| frame.locals.iter().map(|(name, _)| name.clone()).collect())
+            })
+            .unwrap_or_default()
+    }
+
+    fn restore_shadowed(&self, name: &str, shadowed: ShadowedVar) {
+        if let Ok(mut vars) = self.vars.write() {
+            match shadowed.var {
+                Some(var) => vars.insert(name.to_string(), var),
+                None => vars.remove(name),
+            };
+        }
+        if let Ok(mut env) = self.env.write() {
+            match shadowed.env {
+                Some(value) => env.insert(name.to_string(), value),
+                None => env.remove(name),
+            };
+        }
+        if let Ok(mut arrays) = self.arrays.write() {
+            match shadowed.array {
+                Some(array) => arrays.insert(name.to_string(), array),
+                None => arrays.remove(name),
+            };
+        }
+    }
+
+    /// Keep `FUNCNAME` in step with the call stack, innermost call first;
+    /// it is unset outside functions
+    fn refresh_funcname(&self) {
+        let names: Vec<String> = self
+            .call_stack
+            .read()
+            .map(|stack| stack.iter().rev().map(|f| f.function.clone()).collect())
+            .unwrap_or_default();
+        if names.is_empty() {
+            self.unset_var("FUNCNAME");
+        } else {
+            self.set_array("FUNCNAME", ShellArray::from_values(names));
+        }
+    }
+
     /// Set shell variable (not exported to environment)
     pub fn set_shell_var<K>(&self, key: K, var: ShellVariable)
     where
@@ -1314,6 +2023,13 @@ impl ShellContext {
         }
     }
 
+    /// Replace the directory stack, most recent entry last
+    pub fn set_dirs(&self, dirs: Vec<PathBuf>) {
+        if let Ok(mut stack) = self.dir_stack.lock() {
+            *stack = dirs;
+        }
+    }
+
     /// Get directory stack
     pub fn dirs(&self) -> Vec<PathBuf> {
         self.dir_stack
@@ -1334,45 +2050,14 @@ impl ShellContext {
             )
         })?;
 
-        match option {
-            "errexit" | "e" => options.errexit = value,
-            "xtrace" | "x" => options.xtrace = value,
-            "pipefail" => options.pipefail = value,
-            "noclobber" | "C" => options.noclobber = value,
-            "noglob" | "f" => options.noglob = value,
-            "hashall" | "h" => options.hashall = value,
-            "monitor" | "m" => options.monitor = value,
-            "nounset" | "u" => options.nounset = value,
-            "verbose" | "v" => options.verbose = value,
-            "vi" => {
-                options.vi_mode = value;
-                if value {
-                    options.emacs_mode = false;
-                }
-            }
-            "emacs" => {
-                options.emacs_mode = value;
-                if value {
-                    options.vi_mode = false;
-                }
-            }
-            "histexpand" | "H" => options.histexpand = value,
-            "completion" => options.completion = value,
-            "cdspell" => options.cdspell = value,
-            "checkwinsize" => options.checkwinsize = value,
-            "extglob" => options.extglob = value,
-            "nullglob" => options.nullglob = value,
-            "nocaseglob" => options.nocaseglob = value,
-            "dotglob" => options.dotglob = value,
-            _ => {
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
-                    format!("Unknown shell option: {option}"),
-                ))
-            }
+        if options.set(option, value) {
+            Ok(())
+        } else {
+            Err(ShellError::new(
+                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                format!("Unknown shell option: {option}"),
+            ))
         }
-
-        Ok(())
     }
 
     /// Get shell option
@@ -1384,35 +2069,12 @@ impl ShellContext {
             )
         })?;
 
-        let value = match option {
-            "errexit" | "e" => options.errexit,
-            "xtrace" | "x" => options.xtrace,
-            "pipefail" => options.pipefail,
-            "noclobber" | "C" => options.noclobber,
-            "noglob" | "f" => options.noglob,
-            "hashall" | "h" => options.hashall,
-            "monitor" | "m" => options.monitor,
-            "nounset" | "u" => options.nounset,
-            "verbose" | "v" => options.verbose,
-            "vi" => options.vi_mode,
-            "emacs" => options.emacs_mode,
-            "histexpand" | "H" => options.histexpand,
-            "completion" => options.completion,
-            "cdspell" => options.cdspell,
-            "checkwinsize" => options.checkwinsize,
-            "extglob" => options.extglob,
-            "nullglob" => options.nullglob,
-            "nocaseglob" => options.nocaseglob,
-            "dotglob" => options.dotglob,
-            _ => {
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
-                    format!("Unknown shell option: {option}"),
-                ))
-            }
-        };
-
-        Ok(value)
+        options.get(option).ok_or_else(|| {
+            ShellError::new(
+                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                format!("Unknown shell option: {option}"),
+            )
+        })
     }
 
     /// Get all shell options
@@ -1426,6 +2088,15 @@ impl ShellContext {
         Ok(options.clone())
     }
 
+    /// Whether this context belongs to a `( ... )` subshell or another copy
+    /// of the shell that shares the process with its parent
+    pub fn is_subshell(&self) -> bool {
+        self.options
+            .read()
+            .map(|options| options.subshell_level > 0)
+            .unwrap_or(false)
+    }
+
     /// Set exit status of last command
     pub fn set_exit_status(&self, status: i32) {
         if let Ok(mut last_status) = self.last_exit_status.lock() {
@@ -1494,8 +2165,34 @@ impl ShellContext {
         if let (Ok(src), Ok(mut dst)) = (self.options.read(), child.options.write()) {
             *dst = src.clone();
         }
-        // Inherit per-command timeout
+        // Subshells keep ignored signals but drop every other trap
+        if let (Ok(src), Ok(mut dst)) = (self.traps.read(), child.traps.write()) {
+            *dst = src.for_subshell();
+        }
+        if let (Ok(src), Ok(mut dst)) = (self.positional.read(), child.positional.write()) {
+            *dst = src.clone();
+        }
+        if let (Ok(src), Ok(mut dst)) = (self.arrays.read(), child.arrays.write()) {
+            *dst = src.clone();
+        }
+        // `pushd` and `popd` in a subshell leave the parent's stack alone
+        if let (Ok(src), Ok(mut dst)) = (self.dir_stack.lock(), child.dir_stack.lock()) {
+            *dst = src.clone();
+        }
+        // Plugin commands stay runnable in subshells
+        child.plugin_commands = self.plugin_commands.clone();
+        // A subshell inside a function still sees its frames and `FUNCNAME`
+        if let (Ok(src), Ok(mut dst)) = (self.call_stack.read(), child.call_stack.write()) {
+            *dst = src.clone();
+        }
+        // Inherit per-command timeout and the overall deadline
         child.per_command_timeout = self.per_command_timeout;
+        child.global_deadline = self.global_deadline;
+        child.last_background_pid = self.last_background_pid;
+        if let (Ok(src), Ok(mut dst)) = (self.command_hash.lock(), child.command_hash.lock()) {
+            *dst = src.clone();
+        }
+        child.set_exit_status(self.get_exit_status());
         // Reset control flags in child (break/continue are local control flow)
         if let Ok(mut dst) = child.options.write() {
             dst.break_requested = false;
@@ -1603,6 +2300,19 @@ mod tests {
     // Global lock to serialize environment mutations across all tests in this module
     static ENV_TEST_LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
 
+    #[test]
+    fn options_accept_long_names_and_flag_letters() {
+        let mut options = ShellOptions::default();
+        assert!(options.set("e", true));
+        assert!(options.set("pipefail", true));
+        assert_eq!(options.get("errexit"), Some(true));
+        assert_eq!(options.get("pipefail"), Some(true));
+        assert!(options.set("vi", true));
+        assert_eq!(options.get("emacs"), Some(false));
+        assert!(!options.set("bogus", true));
+        assert_eq!(options.get("bogus"), None);
+    }
+
     #[test]
     fn test_detect_login_shell_with_dash_prefix() {
         // Serialize environment mutations to avoid race with other tests (use module-level lock)
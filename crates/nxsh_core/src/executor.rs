@@ -3,9 +3,15 @@
 //! This module provides the core execution engine that can interpret both
 //! AST nodes directly and compiled MIR programs for optimal performance.
 
-use crate::context::ShellContext;
+use crate::context::{split_subscript, ShellArray, ShellContext};
 use crate::error::{ErrorKind, ShellError, ShellResult};
+use crate::hooks::{HookAction, HookEvent, HookKind};
+use crate::job::{JobSignal, JobStatus};
+use crate::mir::profile::Profile;
 use crate::mir::{MirExecutor, MirProgram, MirValue}; // MIR integration
+use crate::stream::{OutputTarget, PipeWriter, Stream, StreamType};
+use crate::structured_data::{PipelineData, StructuredValue};
+use crate::trap::TrapCondition;
 use nxsh_parser::ast::AstNode;
 use nxsh_parser::parse as parse_program;
 // use crate::macros::{MacroSystem, Macro}; // currently unused
@@ -34,10 +40,56 @@ pub(crate) fn simple_unparse(node: &AstNode) -> String {
 }
 // use crate::macros::{MacroSystem, Macro}; // currently unused
 // use crate::macros::{MacroSystem, Macro}; // currently unused
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+/// Index just past the `)` closing a `$(` whose body starts at `start`.
+/// Quoted text and nested parentheses are skipped, as in the grammar.
+fn substitution_close(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            q @ ('\'' | '"') => i += chars.get(i + 1..)?.iter().position(|&c| c == q)? + 1,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i + 1),
+            ')' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether `text` is exactly one unquoted `$(...)` or `` `...` ``
+fn is_bare_substitution(text: &str) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    let end = match chars.as_slice() {
+        ['$', '(', next, ..] if *next != '(' => substitution_close(&chars, 2),
+        ['`', ..] => backquote_close(&chars, 1),
+        _ => None,
+    };
+    end == Some(chars.len())
+}
+
+/// Index just past the unescaped backquote closing a body starting at `start`
+fn backquote_close(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '`' => return Some(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
 /// Execution strategy for shell commands
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExecutionStrategy {
@@ -119,6 +171,51 @@ impl ExecutionResult {
     }
 }
 
+/// How a command line run by `Executor::execute_ast` ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandReport {
+    /// Exit status of the command line; 1 when it failed with an error
+    pub exit_code: i32,
+    /// Wall-clock time from start to finish
+    pub duration: Duration,
+}
+
+/// Callback run after every command line with its report
+pub type PostCommandHook = Arc<dyn Fn(&CommandReport) + Send + Sync>;
+
+/// Post-command hooks, shared between a context and the state it is saved to
+/// so they outlive a single evaluation
+#[derive(Clone, Default)]
+pub struct PostCommandHooks(Arc<RwLock<Vec<PostCommandHook>>>);
+
+impl PostCommandHooks {
+    /// Run `hook` after every following command line
+    pub fn add(&self, hook: PostCommandHook) {
+        if let Ok(mut hooks) = self.0.write() {
+            hooks.push(hook);
+        }
+    }
+
+    /// Report a finished command line to every hook
+    pub fn run(&self, report: &CommandReport) {
+        // Call outside the lock so a hook may register another
+        let hooks = match self.0.read() {
+            Ok(hooks) => hooks.clone(),
+            Err(_) => return,
+        };
+        for hook in hooks {
+            hook(report);
+        }
+    }
+}
+
+impl std::fmt::Debug for PostCommandHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.0.read().map(|hooks| hooks.len()).unwrap_or(0);
+        write!(f, "PostCommandHooks({count})")
+    }
+}
+
 /// Performance metrics for execution analysis
 #[derive(Debug, Clone, Default)]
 pub struct ExecutionMetrics {
@@ -167,10 +264,48 @@ pub trait Builtin: Send + Sync {
     }
 }
 
+/// A builtin that passes typed values along a pipeline instead of text.
+/// When every element of a pipeline is one, each stage receives the value
+/// the previous stage produced and the last value is rendered for the sink:
+/// a table on a terminal, plain text otherwise. The first element may also
+/// be an ordinary command, whose output reaches the next stage as a string.
+/// Outside such pipelines the command of the same name runs as usual.
+pub trait StructuredBuiltin: Send + Sync {
+    /// Name the command is invoked by
+    fn name(&self) -> &'static str;
+
+    /// Run with the previous stage's value, or `None` for the first stage
+    fn run(
+        &self,
+        context: &mut ShellContext,
+        args: &[String],
+        input: Option<PipelineData>,
+    ) -> ShellResult<PipelineData>;
+}
+
+/// Draws a table of headers and rows for the terminal
+pub type TableRenderer = fn(&[String], &[Vec<String>]) -> String;
+
+/// Draws the numbered menu of a `select` loop within the given columns,
+/// followed by its prompt; no items for the prompt alone
+pub type MenuRenderer = fn(&[String], &str, usize) -> String;
+
+/// A structured builtin and the argument nodes it was given
+type StructuredStage<'n, 'src> = (Arc<dyn StructuredBuiltin>, &'n [AstNode<'src>]);
+
+/// An optional ordinary command feeding text to a run of structured stages
+type StructuredStages<'n, 'src> = (Option<&'n AstNode<'src>>, Vec<StructuredStage<'n, 'src>>);
+
 /// Main shell executor with multi-strategy execution support
 pub struct Executor {
     /// Registered builtin commands
     builtins: HashMap<String, Arc<dyn Builtin>>,
+    /// Builtins that exchange typed values inside pipelines
+    structured_builtins: HashMap<String, Arc<dyn StructuredBuiltin>>,
+    /// Renders structured pipeline output on a terminal
+    table_renderer: Option<TableRenderer>,
+    /// Renders the menus and prompts of `select` loops
+    menu_renderer: Option<MenuRenderer>,
     /// Current execution strategy
     strategy: ExecutionStrategy,
     /// Performance statistics
@@ -181,6 +316,44 @@ pub struct Executor {
     cmdsub_cache_map: HashMap<String, ExecutionResult>,
     cmdsub_cache_order: VecDeque<String>,
     cmdsub_cache_capacity: usize,
+    /// Nesting depth of running trap actions (traps do not re-trigger traps)
+    trap_depth: usize,
+    /// Nesting depth of running hooks (hooks do not fire hooks)
+    hook_depth: usize,
+    /// Nesting depth of tested contexts (if/while conditions, `&&`/`||` left
+    /// operands) where a failing command must not fire the ERR trap
+    condition_depth: usize,
+    /// Where commands send their stdout: the terminal, a command
+    /// substitution collecting it, or the pipe to the next pipeline stage
+    output: OutputTarget,
+    /// Pipe the running pipeline stage reads, which external commands it
+    /// starts read in place of the shell's own stdin
+    input: Option<File>,
+    /// Time limit `timeout` set on the command it runs
+    deadline: Option<Deadline>,
+    /// Priority and CPUs `nice` set for the programs its command starts
+    scheduling: Option<nxsh_hal::Scheduling>,
+    /// Set when a command failed under `set -e`; unwinds the current run
+    errexit_pending: bool,
+    /// Set by `return`; unwinds to the enclosing function call or sourced file
+    return_pending: bool,
+    /// Nesting depth of function calls and sourced files, which `return` can leave
+    return_depth: usize,
+    /// Print MIR programs before and after optimization (`NXSH_MIR_DUMP`)
+    mir_dump: bool,
+    /// Profile recorded by the last `profile run`
+    last_profile: Option<Profile>,
+}
+
+/// A time limit on a command: when it passes the command's process group
+/// gets `signal`, then KILL once `kill_after` more has gone by
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    at: Instant,
+    signal: JobSignal,
+    kill_after: Option<Duration>,
+    /// Report each signal sent on stderr (`timeout -v`)
+    verbose: bool,
 }
 
 /// Executor performance statistics
@@ -199,13 +372,66 @@ pub struct ExecutorStats {
 }
 
 impl Executor {
-    /// Public interface to execute an AST node
+    /// Public interface to execute an AST node. The context's post-command
+    /// hooks are told how it ended and how long it took, and its
+    /// `pre_command`, `post_command` and `cwd_changed` hooks run around it.
     pub fn execute_ast(
         &mut self,
         ast: &AstNode,
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        self.execute_ast_direct(ast, context)
+        self.errexit_pending = false;
+        self.return_pending = false;
+        let command = if context.hooks.has(HookKind::PreCommand)
+            || context.hooks.has(HookKind::PostCommand)
+        {
+            ast.to_string()
+        } else {
+            String::new()
+        };
+        let old_cwd = context.cwd.clone();
+        let before = self.run_hooks(
+            &HookEvent::PreCommand {
+                command: command.clone(),
+            },
+            context,
+        );
+
+        let started = Instant::now();
+        let result = self.execute_ast_direct(ast, context);
+        let report = CommandReport {
+            exit_code: result.as_ref().map_or(1, |r| r.exit_code),
+            duration: started.elapsed(),
+        };
+        context.post_command_hooks.run(&report);
+
+        let mut after = self.run_hooks(
+            &HookEvent::PostCommand {
+                command,
+                exit_code: report.exit_code,
+                duration: report.duration,
+            },
+            context,
+        );
+        if context.cwd != old_cwd {
+            let event = HookEvent::CwdChanged {
+                old: old_cwd.display().to_string(),
+                new: context.cwd.display().to_string(),
+            };
+            Self::merge_trap_output(&mut after, Some(self.run_hooks(&event, context)));
+        }
+        result.map(|mut result| {
+            result.stdout.insert_str(0, &before.stdout);
+            result.stderr.insert_str(0, &before.stderr);
+            Self::merge_trap_output(&mut result, Some(after));
+            result
+        })
+    }
+
+    /// True while `set -e` or `return` is unwinding the current run, so
+    /// lists and loops stop before their next command
+    fn unwinding(&self) -> bool {
+        self.errexit_pending || self.return_pending
     }
 
     fn cmdsub_cache_get(&mut self, key: &str) -> Option<ExecutionResult> {
@@ -257,11 +483,26 @@ impl Executor {
                 metrics: ExecutionMetrics::default(),
             });
         }
+        // Only literal bodies are side-effect free, so only their output may be reused
+        let cacheable = matches!(
+            command,
+            AstNode::Word(_) | AstNode::StringLiteral { .. } | AstNode::NumberLiteral { .. }
+        );
         let key = simple_unparse(command);
-        if let Some(hit) = self.cmdsub_cache_get(&key) {
-            return Ok(hit);
+        if cacheable {
+            if let Some(hit) = self.cmdsub_cache_get(&key) {
+                return Ok(hit);
+            }
         }
-        let res = self.execute_ast_direct(command, context)?;
+        // Builtins writing to `ctx.stdout` land in a byte stream instead of the terminal
+        let captured = Stream::new(StreamType::Byte);
+        let saved_stdout = std::mem::replace(&mut context.stdout, Box::new(captured.clone()));
+        let saved_output = std::mem::replace(&mut self.output, OutputTarget::Capture);
+        // Failures inside the substitution neither fire ERR nor trip `set -e`
+        let res = self.execute_tested(command, context);
+        self.output = saved_output;
+        context.stdout = saved_stdout;
+        let mut res = res?;
         if context.is_timed_out() {
             return Ok(ExecutionResult {
                 exit_code: 124,
@@ -272,9 +513,156 @@ impl Executor {
                 metrics: ExecutionMetrics::default(),
             });
         }
-        self.cmdsub_cache_put(key, res.clone());
+        let written = captured.to_bytes()?;
+        if !written.is_empty() {
+            res.stdout.insert_str(0, &String::from_utf8_lossy(&written));
+        }
+        // `$?` after `x=$(cmd)` reports the substituted command
+        context.set_exit_status(res.exit_code);
+        if cacheable {
+            self.cmdsub_cache_put(key, res.clone());
+        }
         Ok(res)
     }
+
+    /// Fields produced by `$(cmd)` / `` `cmd` ``: trailing newlines are stripped,
+    /// and the output is split on IFS for backticks or when `NXSH_SUBST_SPLIT=1`.
+    /// Quoted substitutions never reach here; they stay a single field.
+    fn substitution_fields(
+        &mut self,
+        command: &AstNode,
+        is_legacy: bool,
+        context: &mut ShellContext,
+    ) -> Vec<String> {
+        let Ok(r) = self.eval_cmd_substitution(command, context) else {
+            return vec![String::new()];
+        };
+        let output = Self::captured_output(r, context);
+        let should_split = is_legacy || context.get_var("NXSH_SUBST_SPLIT").as_deref() == Some("1");
+        if !should_split {
+            return vec![output];
+        }
+        let fields = Self::split_ifs(&output, context);
+        if fields.is_empty() {
+            vec![String::new()]
+        } else {
+            fields
+        }
+    }
+
+    /// Split substitution output on `NXSH_IFS` (falling back to `IFS`), dropping empty fields
+    fn split_ifs(output: &str, context: &ShellContext) -> Vec<String> {
+        let ifs = context
+            .get_var("NXSH_IFS")
+            .or_else(|| context.get_var("IFS"))
+            .unwrap_or_else(|| " \t\n".to_string());
+        output
+            .split(|c| ifs.contains(c))
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Output of a finished substitution with trailing newlines stripped;
+    /// `NXSH_SUBST_STDERR=merge` appends stderr (default: separate)
+    fn captured_output(r: ExecutionResult, context: &ShellContext) -> String {
+        let mut merged = r.stdout;
+        let stderr_mode = context
+            .get_var("NXSH_SUBST_STDERR")
+            .unwrap_or_else(|| "separate".to_string());
+        if stderr_mode.eq_ignore_ascii_case("merge") && !r.stderr.is_empty() {
+            if !merged.is_empty() && !merged.ends_with('\n') {
+                merged.push('\n');
+            }
+            merged.push_str(&r.stderr);
+        }
+        let len = merged.trim_end_matches('\n').len();
+        merged.truncate(len);
+        merged
+    }
+
+    /// Expand a word's text like an assignment value, running any command
+    /// substitutions in it first; the result is a single field
+    fn expand_word_text(&mut self, text: &str, context: &mut ShellContext) -> ShellResult<String> {
+        let (text, _) = self.splice_substitutions(text, context)?;
+        Self::expand_value_text(&text, context)
+    }
+
+    /// A word argument with substitutions embedded in it (`"$(cmd)"`,
+    /// `$(cmd).txt`): quotes are removed and the whole word stays one field.
+    /// Words where nothing ran (`'$(x)'`, `$((1))`) are kept verbatim.
+    fn substituted_word(&mut self, word: &str, context: &mut ShellContext) -> ShellResult<String> {
+        match self.splice_substitutions(word, context)? {
+            (spliced, Some(_)) => Self::expand_value_text(&spliced, context),
+            (_, None) => Ok(word.to_string()),
+        }
+    }
+
+    /// Run each `$(cmd)` / `` `cmd` `` embedded in `text` (outside single
+    /// quotes) and splice in its output, backslash-escaped so that
+    /// `expand_value_text` keeps it literal. Also returns the exit status of
+    /// the last substitution, or `None` when nothing ran.
+    fn splice_substitutions(
+        &mut self,
+        text: &str,
+        context: &mut ShellContext,
+    ) -> ShellResult<(String, Option<i32>)> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut status = None;
+        let mut in_single = false;
+        let mut in_double = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let span = match c {
+                // `$((` starts arithmetic, not a substitution
+                '$' if !in_single
+                    && chars.get(i + 1) == Some(&'(')
+                    && chars.get(i + 2) != Some(&'(') =>
+                {
+                    substitution_close(&chars, i + 2)
+                        .map(|end| (chars[i + 2..end - 1].iter().collect::<String>(), end))
+                }
+                '`' if !in_single => backquote_close(&chars, i + 1).map(|end| {
+                    let body: String = chars[i + 1..end - 1].iter().collect();
+                    (nxsh_parser::unescape_backquoted(&body), end)
+                }),
+                _ => None,
+            };
+            let Some((body, end)) = span else {
+                match c {
+                    '\\' if !in_single && i + 1 < chars.len() => {
+                        out.push(c);
+                        i += 1;
+                    }
+                    '\'' if !in_double => in_single = !in_single,
+                    '"' if !in_single => in_double = !in_double,
+                    _ => {}
+                }
+                out.push(chars[i]);
+                i += 1;
+                continue;
+            };
+            let ast = parse_program(&body).map_err(|e| {
+                ShellError::new(
+                    ErrorKind::ParseError(crate::error::ParseErrorKind::SyntaxError),
+                    format!("command substitution: {e}"),
+                )
+            })?;
+            let result = self.eval_cmd_substitution(&ast, context)?;
+            status = Some(result.exit_code);
+            for ch in Self::captured_output(result, context).chars() {
+                if "$`\"\\".contains(ch) || (ch == '\'' && !in_double) {
+                    out.push('\\');
+                }
+                out.push(ch);
+            }
+            i = end;
+        }
+        Ok((out, status))
+    }
+
     // Simple filename glob / extglob subset expansion (no directory components yet).
     // Supports: *, ?, [abc] character classes. Extglob subset patterns *(alt1|alt2), +(alt), ?(alt), @(alt), !(alt) are
     // approximated into a small candidate set before standard wildcard matching. Safety caps: max 256 matches.
@@ -584,12 +972,27 @@ impl Executor {
         eprintln!("DEBUG: Creating comprehensive Executor with ALL builtins");
         let mut executor = Self {
             builtins: HashMap::new(),
+            structured_builtins: HashMap::new(),
+            table_renderer: None,
+            menu_renderer: None,
             strategy: ExecutionStrategy::DirectInterpreter,
             stats: ExecutorStats::default(),
             mir_executor: MirExecutor::new(),
             cmdsub_cache_map: HashMap::new(),
             cmdsub_cache_order: VecDeque::new(),
             cmdsub_cache_capacity: 128,
+            trap_depth: 0,
+            hook_depth: 0,
+            condition_depth: 0,
+            output: OutputTarget::Inherit,
+            input: None,
+            deadline: None,
+            scheduling: None,
+            errexit_pending: false,
+            return_pending: false,
+            return_depth: 0,
+            mir_dump: false,
+            last_profile: None,
         };
 
         // COMPLETE builtin registration as specified - NO deferred loading
@@ -603,12 +1006,27 @@ impl Executor {
         // eprintln!("DEBUG: Creating new Executor");
         let mut executor = Self {
             builtins: HashMap::new(),
+            structured_builtins: HashMap::new(),
+            table_renderer: None,
+            menu_renderer: None,
             strategy: ExecutionStrategy::DirectInterpreter,
             stats: ExecutorStats::default(),
             mir_executor: MirExecutor::new(),
             cmdsub_cache_map: HashMap::new(),
             cmdsub_cache_order: VecDeque::new(),
             cmdsub_cache_capacity: 128,
+            trap_depth: 0,
+            hook_depth: 0,
+            condition_depth: 0,
+            output: OutputTarget::Inherit,
+            input: None,
+            deadline: None,
+            scheduling: None,
+            errexit_pending: false,
+            return_pending: false,
+            return_depth: 0,
+            mir_dump: false,
+            last_profile: None,
         };
 
         // Register built-in commands
@@ -631,10 +1049,40 @@ impl Executor {
         {
             executor.strategy = ExecutionStrategy::MirEngine;
         }
+        executor.mir_dump = std::env::var_os("NXSH_MIR_DUMP").is_some_and(|v| v != "0");
 
         executor
     }
 
+    /// Executor for a pipeline stage running on its own thread: the same
+    /// commands and settings, with empty caches and nothing pending
+    fn for_pipeline_stage(&self) -> Self {
+        Self {
+            builtins: self.builtins.clone(),
+            structured_builtins: self.structured_builtins.clone(),
+            table_renderer: self.table_renderer,
+            menu_renderer: self.menu_renderer,
+            strategy: self.strategy,
+            stats: ExecutorStats::default(),
+            mir_executor: MirExecutor::new(),
+            cmdsub_cache_map: HashMap::new(),
+            cmdsub_cache_order: VecDeque::new(),
+            cmdsub_cache_capacity: self.cmdsub_cache_capacity,
+            trap_depth: self.trap_depth,
+            hook_depth: self.hook_depth,
+            condition_depth: self.condition_depth,
+            output: OutputTarget::Inherit,
+            input: None,
+            deadline: self.deadline,
+            scheduling: self.scheduling.clone(),
+            errexit_pending: false,
+            return_pending: false,
+            return_depth: 0,
+            mir_dump: self.mir_dump,
+            last_profile: None,
+        }
+    }
+
     /// Register all built-in commands
     fn register_all_builtins(&mut self) {
         // Use standard builtins from nxsh_core
@@ -655,11 +1103,32 @@ impl Executor {
         self.builtins.insert(name, builtin);
     }
 
+    /// Register a builtin that exchanges typed values inside pipelines
+    pub fn register_structured_builtin(&mut self, builtin: Arc<dyn StructuredBuiltin>) {
+        let name = builtin.name().to_string();
+        self.structured_builtins.insert(name, builtin);
+    }
+
+    /// Use `renderer` to draw structured pipeline output on a terminal
+    pub fn set_table_renderer(&mut self, renderer: TableRenderer) {
+        self.table_renderer = Some(renderer);
+    }
+
+    /// Use `renderer` to draw the menus and prompts of `select` loops
+    pub fn set_menu_renderer(&mut self, renderer: MenuRenderer) {
+        self.menu_renderer = Some(renderer);
+    }
+
     /// Set the execution strategy
     pub fn set_strategy(&mut self, strategy: ExecutionStrategy) {
         self.strategy = strategy;
     }
 
+    /// Strategy `execute` runs commands with
+    pub fn strategy(&self) -> ExecutionStrategy {
+        self.strategy
+    }
+
     /// Execute an AST node with the current strategy
     pub fn execute(
         &mut self,
@@ -680,6 +1149,8 @@ impl Executor {
             });
         }
 
+        self.errexit_pending = false;
+        self.return_pending = false;
         // Execute according to strategy, but do not early-return on error so we can update stats
         let result: ShellResult<ExecutionResult> = match self.strategy {
             ExecutionStrategy::DirectInterpreter => self.execute_ast_direct(node, context),
@@ -756,7 +1227,7 @@ impl Executor {
     fn execute_with_mir(
         &mut self,
         node: &AstNode,
-        _context: &mut ShellContext,
+        context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
         let start_time = Instant::now();
 
@@ -764,20 +1235,80 @@ impl Executor {
         let compile_start = Instant::now();
         let mir_program = self.compile_ast_to_mir(node)?;
         let compile_time = compile_start.elapsed().as_micros() as u64;
+        if self.mir_dump {
+            eprintln!("; MIR before optimization\n{mir_program}");
+        }
 
         // Optimize MIR program
         let optimize_start = Instant::now();
         let (optimized_program, memory_usage) = self.optimize_mir_program(mir_program)?;
         let optimize_time = optimize_start.elapsed().as_micros() as u64;
+        if self.mir_dump {
+            eprintln!("; MIR after optimization\n{optimized_program}");
+        }
+
+        let metrics = ExecutionMetrics {
+            compile_time_us: compile_time,
+            optimize_time_us: optimize_time,
+            memory_usage,
+            ..ExecutionMetrics::default()
+        };
+        self.run_mir_program(&optimized_program, context, start_time, metrics)
+    }
+
+    /// Execute an already compiled and optimized MIR program, such as one
+    /// loaded from the script cache
+    pub fn execute_compiled_mir(
+        &mut self,
+        program: &MirProgram,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        if self.mir_dump {
+            eprintln!("; MIR from cache\n{program}");
+        }
+        self.run_mir_program(
+            program,
+            context,
+            Instant::now(),
+            ExecutionMetrics::default(),
+        )
+    }
 
-        // Execute optimized MIR program
+    /// Run `program` from its main function, with its commands dispatched to
+    /// this executor's builtins and `context`, and convert its result
+    fn run_mir_program(
+        &mut self,
+        program: &MirProgram,
+        context: &mut ShellContext,
+        start_time: Instant,
+        metrics: ExecutionMetrics,
+    ) -> ShellResult<ExecutionResult> {
         let execute_start = Instant::now();
-        let result_value = self.mir_executor.execute(&optimized_program).map_err(|e| {
-            ShellError::new(
-                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::CommandNotFound),
-                format!("MIR execution failed: {e}"),
-            )
-        })?;
+        // FUNCNEST limits function nesting as in bash when set to a positive number
+        let max_call_depth = context
+            .get_var("FUNCNEST")
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|&depth| depth > 0)
+            .unwrap_or(crate::mir::DEFAULT_MAX_CALL_DEPTH);
+        self.mir_executor.set_max_call_depth(max_call_depth);
+        let mut host = crate::mir::commands::ShellCommands::new(&self.builtins, context);
+        let outcome = self.mir_executor.execute_main_with(program, &mut host);
+        drop(host);
+        let mut stdout = self.mir_executor.take_output();
+        let stderr = self.mir_executor.take_error_output();
+        let result_value = match outcome {
+            Ok(value) => value,
+            Err(e) => {
+                use std::io::Write;
+                // What ran before the failure has still been printed
+                let _ = context.stdout.write_all(stdout.as_bytes());
+                let _ = context.stderr.write_all(stderr.as_bytes());
+                return Err(ShellError::new(
+                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::CommandNotFound),
+                    format!("MIR execution failed: {e}"),
+                ));
+            }
+        };
 
         let execute_time = execute_start.elapsed().as_micros() as u64;
         let total_time = start_time.elapsed().as_micros() as u64;
@@ -790,634 +1321,95 @@ impl Executor {
             _ => 0,
         };
 
-        let stdout = match result_value {
-            MirValue::String(s) => s,
-            _ => String::new(),
-        };
+        if let MirValue::String(s) = result_value {
+            stdout.push_str(&s);
+        }
 
         Ok(ExecutionResult {
             exit_code,
             stdout,
-            stderr: String::new(),
+            stderr,
             execution_time: total_time,
             strategy: ExecutionStrategy::MirEngine,
             metrics: ExecutionMetrics {
-                compile_time_us: compile_time,
-                optimize_time_us: optimize_time,
                 execute_time_us: execute_time,
                 instruction_count: self.mir_executor.stats().instructions_executed,
-                memory_usage,
+                ..metrics
             },
         })
     }
 
     /// Compile AST node to MIR program
     fn compile_ast_to_mir(&self, node: &AstNode) -> ShellResult<MirProgram> {
-        let mut program = MirProgram::new();
-        let mut main_func = crate::mir::MirFunction::new("main".to_string(), vec![]);
-
-        // Convert AST to MIR instructions (comprehensive implementation)
-        let mut entry_block = crate::mir::MirBasicBlock::new(0);
-
-        match node {
-            AstNode::Program(statements) => {
-                // Compile multiple statements sequentially
-                let mut last_result_reg = main_func.allocate_register();
-
-                for statement in statements {
-                    let result_reg =
-                        self.compile_statement_to_mir(statement, &mut main_func, &mut entry_block)?;
-                    last_result_reg = result_reg;
-                }
-
-                // Return last result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(last_result_reg)),
-                });
-            }
-            AstNode::Command { name, args, .. } => {
-                // Compile single command
-                let result_reg =
-                    self.compile_command_to_mir(name, args, &mut main_func, &mut entry_block)?;
-
-                // Return command result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(result_reg)),
-                });
-            }
-            AstNode::Pipeline { elements, .. } => {
-                // Compile pipeline execution
-                let result_reg =
-                    self.compile_pipeline_to_mir(elements, &mut main_func, &mut entry_block)?;
-
-                // Return pipeline result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(result_reg)),
-                });
-            }
-            AstNode::If {
-                condition,
-                then_branch,
-                else_branch,
-                ..
-            } => {
-                // Compile conditional execution
-                let result_reg = self.compile_conditional_to_mir(
-                    condition,
-                    then_branch,
-                    else_branch.as_deref(),
-                    &mut main_func,
-                    &mut entry_block,
-                    &mut program,
-                )?;
-
-                // Return conditional result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(result_reg)),
-                });
-            }
-            AstNode::For {
-                variable,
-                iterable,
-                body,
-                ..
-            } => {
-                // Compile loop execution
-                let result_reg = self.compile_loop_to_mir(
-                    variable,
-                    iterable,
-                    body,
-                    &mut main_func,
-                    &mut entry_block,
-                    &mut program,
-                )?;
-
-                // Return loop result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(result_reg)),
-                });
-            }
-            AstNode::Subshell(subshell_commands) => {
-                // Compile subshell execution
-                let commands = match subshell_commands.as_ref() {
-                    AstNode::Program(statements) => statements,
-                    single_command => {
-                        let temp_vec = vec![single_command.clone()];
-                        return self.compile_ast_to_mir(&AstNode::Program(temp_vec));
-                    }
-                };
-
-                let result_reg =
-                    self.compile_subshell_to_mir(commands, &mut main_func, &mut entry_block)?;
-
-                // Return subshell result
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(result_reg)),
-                });
-            }
-            _ => {
-                // For unsupported AST nodes, create simple success return
-                let reg0 = main_func.allocate_register();
-                entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: reg0.clone(),
-                    value: MirValue::Integer(0),
-                });
-                entry_block.add_instruction(crate::mir::MirInstruction::Return {
-                    value: Some(MirValue::Register(reg0)),
-                });
-            }
-        }
-
-        main_func.add_basic_block(entry_block);
-        program.add_function(main_func);
-
-        Ok(program)
+        Ok(crate::mir::lower::Lowerer::new().lower_program(node))
     }
 
     /// Optimize MIR program for better performance
     fn optimize_mir_program(&self, mut program: MirProgram) -> ShellResult<(MirProgram, u64)> {
-        let mut memory_usage = 0u64;
-
-        // Apply optimization passes to all functions
-        let function_names: Vec<String> = program.functions.keys().cloned().collect();
-        for function_name in function_names {
-            if let Some(function) = program.get_function_mut(&function_name) {
-                memory_usage += self.optimize_function(function)?;
-            }
-        }
-
-        Ok((program, memory_usage))
+        let stats = crate::mir::optimizer::optimize_program(&mut program);
+        // Rough estimate of the memory saved: 32 bytes per removed instruction
+        Ok((program, stats.instructions_removed as u64 * 32))
     }
 
-    /// Optimize a single MIR function
-    fn optimize_function(&self, function: &mut crate::mir::MirFunction) -> ShellResult<u64> {
-        let mut memory_saved = 0u64;
-
-        // Dead code elimination
-        memory_saved += self.eliminate_dead_code(function)?;
-
-        // Constant folding
-        memory_saved += self.constant_folding(function)?;
-
-        // Register allocation optimization
-        memory_saved += self.optimize_register_allocation(function)?;
-
-        Ok(memory_saved)
-    }
+    /// Execute MIR program directly
+    pub fn execute_mir_program(&mut self, program: &MirProgram) -> ShellResult<ExecutionResult> {
+        let start_time = Instant::now();
 
-    /// Eliminate dead code from MIR function
-    fn eliminate_dead_code(&self, function: &mut crate::mir::MirFunction) -> ShellResult<u64> {
-        let mut instructions_removed = 0u64;
-
-        // Access basic blocks through iteration
-        let block_ids: Vec<u32> = function.blocks.keys().cloned().collect();
-        for block_id in block_ids {
-            if let Some(block) = function.blocks.get_mut(&block_id) {
-                // Remove unreachable instructions after return statements
-                let mut new_instructions = Vec::new();
-                let mut hit_return = false;
-
-                for instruction in &block.instructions {
-                    if hit_return {
-                        instructions_removed += 1;
-                        continue;
-                    }
+        let result_value = self.mir_executor.execute(program).map_err(|e| {
+            ShellError::new(
+                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::CommandNotFound),
+                format!("MIR execution failed: {e}"),
+            )
+        })?;
 
-                    if matches!(instruction, crate::mir::MirInstruction::Return { .. }) {
-                        hit_return = true;
-                    }
+        let execution_time = start_time.elapsed().as_micros() as u64;
 
-                    new_instructions.push(instruction.clone());
-                }
+        // Convert MIR result to ExecutionResult
+        let exit_code = match result_value {
+            MirValue::Integer(code) => code as i32,
+            MirValue::Boolean(true) => 0,
+            MirValue::Boolean(false) => 1,
+            _ => 0,
+        };
 
-                block.instructions = new_instructions;
-            }
+        let mut stdout = self.mir_executor.take_output();
+        if let MirValue::String(s) = result_value {
+            stdout.push_str(&s);
         }
 
-        // Estimate memory saved (rough calculation)
-        Ok(instructions_removed * 32) // Assume 32 bytes per instruction
-    }
-
-    /// Perform constant folding optimization
-    fn constant_folding(&self, _function: &mut crate::mir::MirFunction) -> ShellResult<u64> {
-        // Simple constant folding implementation
-        // In a real implementation, this would fold constant arithmetic operations
-        Ok(0)
+        Ok(ExecutionResult {
+            exit_code,
+            stdout,
+            stderr: self.mir_executor.take_error_output(),
+            execution_time,
+            strategy: ExecutionStrategy::MirEngine,
+            metrics: ExecutionMetrics {
+                compile_time_us: 0,
+                optimize_time_us: 0,
+                execute_time_us: execution_time,
+                instruction_count: self.mir_executor.stats().instructions_executed,
+                memory_usage: 0,
+            },
+        })
     }
 
-    /// Optimize register allocation
-    fn optimize_register_allocation(
-        &self,
-        _function: &mut crate::mir::MirFunction,
-    ) -> ShellResult<u64> {
-        // Register allocation optimization would reduce register pressure
-        // For now, return estimated memory savings
-        Ok(64) // Rough estimate
+    /// Get MIR executor statistics
+    pub fn mir_stats(&self) -> &crate::mir::ExecutionStats {
+        self.mir_executor.stats()
     }
 
-    /// Compile a single statement to MIR instructions
-    fn compile_statement_to_mir(
-        &self,
-        statement: &AstNode,
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        match statement {
-            AstNode::Command { name, args, .. } => {
-                self.compile_command_to_mir(name, args, main_func, entry_block)
-            }
-            AstNode::Assignment { .. } => {
-                // Compile assignment statement
-                let reg = main_func.allocate_register();
-                entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: reg.clone(),
-                    value: MirValue::Integer(0),
-                });
-                Ok(reg)
-            }
-            _ => {
-                // For other statements, return success
-                let reg = main_func.allocate_register();
-                entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: reg.clone(),
-                    value: MirValue::Integer(0),
-                });
-                Ok(reg)
-            }
-        }
+    /// Reset MIR executor statistics
+    pub fn reset_mir_stats(&mut self) {
+        self.mir_executor.reset_stats();
     }
 
-    /// Compile a command to MIR instructions
-    fn compile_command_to_mir(
-        &self,
-        name: &AstNode,
-        args: &[AstNode],
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        let reg0 = main_func.allocate_register();
-        let reg1 = main_func.allocate_register();
-
-        // Load command name
-        let name_str = match name {
-            AstNode::SimpleCommand { name, .. } => name,
-            AstNode::Word(word) => word,
-            _ => "unknown",
-        };
-
-        entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-            dest: reg0.clone(),
-            value: MirValue::String(name_str.to_string()),
-        });
-
-        // Compile arguments
-        let mut arg_regs = Vec::new();
-        for arg in args {
-            let arg_reg = main_func.allocate_register();
-            match arg {
-                AstNode::Word(word) => {
-                    entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                        dest: arg_reg.clone(),
-                        value: MirValue::String(word.to_string()),
-                    });
-                }
-                AstNode::StringLiteral { value, .. } => {
-                    entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                        dest: arg_reg.clone(),
-                        value: MirValue::String(value.to_string()),
-                    });
-                }
-                AstNode::NumberLiteral { value, .. } => {
-                    // Convert string to integer
-                    let int_value = value.parse::<i64>().unwrap_or(0);
-                    entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                        dest: arg_reg.clone(),
-                        value: MirValue::Integer(int_value),
-                    });
-                }
-                _ => {
-                    entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                        dest: arg_reg.clone(),
-                        value: MirValue::String("".to_string()),
-                    });
-                }
-            }
-            arg_regs.push(MirValue::Register(arg_reg));
-        }
-
-        // Execute command
-        entry_block.add_instruction(crate::mir::MirInstruction::ExecuteCommand {
-            dest: reg1.clone(),
-            command: name_str.to_string(),
-            args: arg_regs,
-        });
-
-        Ok(reg1)
-    }
-
-    /// Compile a pipeline to MIR instructions
-    fn compile_pipeline_to_mir(
-        &self,
-        elements: &[AstNode],
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        let mut last_result = main_func.allocate_register();
-
-        // Initialize with success
-        entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-            dest: last_result.clone(),
-            value: MirValue::Integer(0),
-        });
-
-        // Process each pipeline element
-        for element in elements {
-            match element {
-                AstNode::Command { name, args, .. } => {
-                    last_result =
-                        self.compile_command_to_mir(name, args, main_func, entry_block)?;
-                }
-                _ => {
-                    // For non-command elements, just continue
-                }
-            }
-        }
-
-        Ok(last_result)
-    }
-
-    /// Compile conditional (if/else) to MIR instructions
-    fn compile_conditional_to_mir(
-        &self,
-        condition: &AstNode,
-        then_branch: &AstNode,
-        else_branch: Option<&AstNode>,
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-        _program: &mut MirProgram,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        // Improved conditional compilation with proper condition evaluation
-        let condition_reg = self.compile_condition_to_mir(condition, main_func, entry_block)?;
-        let result_reg = main_func.allocate_register();
-
-        // Create basic blocks for then/else branches
-        let then_block_id = main_func.blocks.len() as u32 + 1;
-        let else_block_id = main_func.blocks.len() as u32 + 2;
-        let end_block_id = main_func.blocks.len() as u32 + 3;
-
-        // Add conditional branch instruction
-        entry_block.add_instruction(crate::mir::MirInstruction::Branch {
-            condition: crate::mir::MirValue::Register(condition_reg),
-            true_block: then_block_id,
-            false_block: else_block_id,
-        });
-
-        // Compile then branch
-        let mut then_block = crate::mir::MirBasicBlock::new(then_block_id);
-        let then_result = self.compile_ast_to_mir_block(then_branch, main_func, &mut then_block)?;
-        then_block.add_instruction(crate::mir::MirInstruction::Move {
-            dest: result_reg.clone(),
-            src: then_result,
-        });
-        then_block.add_instruction(crate::mir::MirInstruction::Jump {
-            target: end_block_id,
-        });
-        main_func.blocks.insert(then_block_id, then_block);
-
-        // Compile else branch
-        let mut else_block = crate::mir::MirBasicBlock::new(else_block_id);
-        let else_result = if let Some(else_branch) = else_branch {
-            self.compile_ast_to_mir_block(else_branch, main_func, &mut else_block)?
-        } else {
-            // Default success value if no else branch
-            let default_reg = main_func.allocate_register();
-            else_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                dest: default_reg.clone(),
-                value: crate::mir::MirValue::Integer(0),
-            });
-            default_reg
-        };
-        else_block.add_instruction(crate::mir::MirInstruction::Move {
-            dest: result_reg.clone(),
-            src: else_result,
-        });
-        else_block.add_instruction(crate::mir::MirInstruction::Jump {
-            target: end_block_id,
-        });
-        main_func.blocks.insert(else_block_id, else_block);
-
-        // Create end block
-        let end_block = crate::mir::MirBasicBlock::new(end_block_id);
-        main_func.blocks.insert(end_block_id, end_block);
-
-        Ok(result_reg)
-    }
-
-    /// Compile condition evaluation to MIR
-    fn compile_condition_to_mir(
-        &self,
-        condition: &AstNode,
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        match condition {
-            AstNode::Command { name, args, .. } => {
-                // Execute command and use exit code as condition
-                let cmd_result = self.compile_command_to_mir(name, args, main_func, entry_block)?;
-                let condition_reg = main_func.allocate_register();
-
-                // Convert exit code to boolean (0 = true, non-zero = false)
-                entry_block.add_instruction(crate::mir::MirInstruction::Equal {
-                    dest: condition_reg.clone(),
-                    left: crate::mir::MirValue::Register(cmd_result),
-                    right: crate::mir::MirValue::Integer(0),
-                });
-
-                Ok(condition_reg)
-            }
-            AstNode::LogicalAnd { left, right } => {
-                let left_reg = self.compile_condition_to_mir(left, main_func, entry_block)?;
-                let right_reg = self.compile_condition_to_mir(right, main_func, entry_block)?;
-                let result_reg = main_func.allocate_register();
-
-                entry_block.add_instruction(crate::mir::MirInstruction::And {
-                    dest: result_reg.clone(),
-                    left: crate::mir::MirValue::Register(left_reg),
-                    right: crate::mir::MirValue::Register(right_reg),
-                });
-
-                Ok(result_reg)
-            }
-            AstNode::LogicalOr { left, right } => {
-                let left_reg = self.compile_condition_to_mir(left, main_func, entry_block)?;
-                let right_reg = self.compile_condition_to_mir(right, main_func, entry_block)?;
-                let result_reg = main_func.allocate_register();
-
-                entry_block.add_instruction(crate::mir::MirInstruction::Or {
-                    dest: result_reg.clone(),
-                    left: crate::mir::MirValue::Register(left_reg),
-                    right: crate::mir::MirValue::Register(right_reg),
-                });
-
-                Ok(result_reg)
-            }
-            _ => {
-                // Default to true for unknown conditions
-                let condition_reg = main_func.allocate_register();
-                entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: condition_reg.clone(),
-                    value: crate::mir::MirValue::Boolean(true),
-                });
-                Ok(condition_reg)
-            }
-        }
-    }
-
-    /// Compile AST node to MIR in a specific block
-    fn compile_ast_to_mir_block(
-        &self,
-        node: &AstNode,
-        main_func: &mut crate::mir::MirFunction,
-        block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        match node {
-            AstNode::Command { name, args, .. } => {
-                self.compile_command_to_mir(name, args, main_func, block)
-            }
-            AstNode::Program(statements) => {
-                let mut last_result = main_func.allocate_register();
-                for statement in statements {
-                    last_result = self.compile_ast_to_mir_block(statement, main_func, block)?;
-                }
-                Ok(last_result)
-            }
-            _ => {
-                // Default success result
-                let result_reg = main_func.allocate_register();
-                block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: result_reg.clone(),
-                    value: crate::mir::MirValue::Integer(0),
-                });
-                Ok(result_reg)
-            }
-        }
-    }
-
-    /// Compile loop to MIR instructions
-    fn compile_loop_to_mir(
-        &self,
-        _variable: &str,
-        _iterable: &AstNode,
-        body: &AstNode,
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-        _program: &mut MirProgram,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        // Simplified loop compilation - just execute body once
-        let result_reg = main_func.allocate_register();
-
-        match body {
-            AstNode::Command { name, args, .. } => {
-                let body_result =
-                    self.compile_command_to_mir(name, args, main_func, entry_block)?;
-                entry_block.add_instruction(crate::mir::MirInstruction::Move {
-                    dest: result_reg.clone(),
-                    src: body_result,
-                });
-            }
-            _ => {
-                entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-                    dest: result_reg.clone(),
-                    value: MirValue::Integer(0),
-                });
-            }
-        }
-
-        Ok(result_reg)
-    }
-
-    /// Compile subshell to MIR instructions
-    fn compile_subshell_to_mir(
-        &self,
-        commands: &[AstNode],
-        main_func: &mut crate::mir::MirFunction,
-        entry_block: &mut crate::mir::MirBasicBlock,
-    ) -> ShellResult<crate::mir::MirRegister> {
-        let mut last_result = main_func.allocate_register();
-
-        // Initialize with success
-        entry_block.add_instruction(crate::mir::MirInstruction::LoadImmediate {
-            dest: last_result.clone(),
-            value: MirValue::Integer(0),
-        });
-
-        // Process each command in subshell
-        for command in commands {
-            last_result = self.compile_statement_to_mir(command, main_func, entry_block)?;
-        }
-
-        Ok(last_result)
-    }
-
-    /// Execute MIR program directly
-    pub fn execute_mir_program(&mut self, program: &MirProgram) -> ShellResult<ExecutionResult> {
-        let start_time = Instant::now();
-
-        let result_value = self.mir_executor.execute(program).map_err(|e| {
-            ShellError::new(
-                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::CommandNotFound),
-                format!("MIR execution failed: {e}"),
-            )
-        })?;
-
-        let execution_time = start_time.elapsed().as_micros() as u64;
-
-        // Convert MIR result to ExecutionResult
-        let exit_code = match result_value {
-            MirValue::Integer(code) => code as i32,
-            MirValue::Boolean(true) => 0,
-            MirValue::Boolean(false) => 1,
-            _ => 0,
-        };
-
-        let stdout = match result_value {
-            MirValue::String(s) => s,
-            _ => String::new(),
-        };
-
-        Ok(ExecutionResult {
-            exit_code,
-            stdout,
-            stderr: String::new(),
-            execution_time,
-            strategy: ExecutionStrategy::MirEngine,
-            metrics: ExecutionMetrics {
-                compile_time_us: 0,
-                optimize_time_us: 0,
-                execute_time_us: execution_time,
-                instruction_count: self.mir_executor.stats().instructions_executed,
-                memory_usage: 0,
-            },
-        })
-    }
-
-    /// Get MIR executor statistics
-    pub fn mir_stats(&self) -> &crate::mir::ExecutionStats {
-        self.mir_executor.stats()
-    }
-
-    /// Reset MIR executor statistics
-    pub fn reset_mir_stats(&mut self) {
-        self.mir_executor.reset_stats();
-    }
-
-    /// Execute AST node directly without MIR compilation
-    fn execute_ast_direct(
-        &mut self,
-        node: &AstNode,
-        context: &mut ShellContext,
-    ) -> ShellResult<ExecutionResult> {
-        let start_time = Instant::now();
+    /// Execute AST node directly without MIR compilation
+    fn execute_ast_direct(
+        &mut self,
+        node: &AstNode,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let start_time = Instant::now();
 
         // Global timeout check (fast-fail before deeper recursion)
         if context.is_timed_out() {
@@ -1467,6 +1459,7 @@ impl Executor {
                     LogicalAnd { .. } => "LogicalAnd",
                     LogicalOr { .. } => "LogicalOr",
                     Sequence { .. } => "Sequence",
+                    JobGroup(_) => "JobGroup",
                     _ => "Other",
                 };
                 eprintln!("AST_DEBUG:{indent}{name}");
@@ -1477,7 +1470,7 @@ impl Executor {
                         debug_variant(s, _depth + 1);
                     }
                 }
-                Pipeline { elements, .. } => {
+                Pipeline { elements, .. } | JobGroup(elements) => {
                     for s in elements {
                         debug_variant(s, _depth + 1);
                     }
@@ -1628,6 +1621,8 @@ impl Executor {
                         });
                     }
                 }
+                let mut stdout = String::new();
+                let mut stderr = String::new();
                 for statement in statements {
                     if context.is_timed_out() {
                         return Ok(ExecutionResult {
@@ -1640,6 +1635,10 @@ impl Executor {
                         });
                     }
                     result = self.execute_ast_direct(statement, context)?;
+                    let signal_traps = self.run_pending_signal_traps(context)?;
+                    Self::merge_trap_output(&mut result, signal_traps);
+                    stdout.push_str(&result.stdout);
+                    stderr.push_str(&result.stderr);
                     if context.is_timed_out() {
                         return Ok(ExecutionResult {
                             exit_code: 124,
@@ -1650,18 +1649,7 @@ impl Executor {
                             metrics: ExecutionMetrics::default(),
                         });
                     }
-                    if result.exit_code != 0 && !context.continue_on_error() {
-                        // If global timeout has already elapsed, prefer 124 over intermediate failures
-                        if context.is_timed_out() {
-                            return Ok(ExecutionResult {
-                                exit_code: 124,
-                                stdout: String::new(),
-                                stderr: "nxsh: execution timed out".to_string(),
-                                execution_time: start_time.elapsed().as_micros() as u64,
-                                strategy: ExecutionStrategy::DirectInterpreter,
-                                metrics: ExecutionMetrics::default(),
-                            });
-                        }
+                    if self.unwinding() {
                         break;
                     }
                 }
@@ -1676,6 +1664,8 @@ impl Executor {
                         metrics: ExecutionMetrics::default(),
                     });
                 }
+                result.stdout = stdout;
+                result.stderr = stderr;
                 result
             }
             AstNode::Sequence { left, right } => {
@@ -1689,7 +1679,10 @@ impl Executor {
                         metrics: ExecutionMetrics::default(),
                     });
                 }
-                let _ = self.execute_ast_direct(left, context)?;
+                let left_res = self.execute_ast_direct(left, context)?;
+                if self.unwinding() {
+                    return Ok(left_res);
+                }
                 if context.is_timed_out() {
                     return Ok(ExecutionResult {
                         exit_code: 124,
@@ -1704,7 +1697,7 @@ impl Executor {
             }
             AstNode::LogicalAnd { left, right } => {
                 // Short-circuit AND: execute right only if left succeeds (exit_code == 0)
-                let left_res = self.execute_ast_direct(left, context)?;
+                let left_res = self.execute_tested(left, context)?;
                 if left_res.exit_code == 0 {
                     self.execute_ast_direct(right, context)?
                 } else {
@@ -1713,40 +1706,33 @@ impl Executor {
             }
             AstNode::LogicalOr { left, right } => {
                 // Short-circuit OR: execute right only if left fails (exit_code != 0)
-                let left_res = self.execute_ast_direct(left, context)?;
+                let left_res = self.execute_tested(left, context)?;
                 if left_res.exit_code != 0 {
                     self.execute_ast_direct(right, context)?
                 } else {
                     left_res
                 }
             }
-            AstNode::Subshell(subshell_commands) => {
-                // Handle subshell execution
-                let commands = match subshell_commands.as_ref() {
-                    AstNode::Program(statements) => statements.clone(),
-                    single_command => vec![single_command.clone()],
-                };
-                self.execute_subshell(&commands, context)?
-            }
+            AstNode::JobGroup(members) => self.execute_job_group(members, context)?,
+            AstNode::Subshell(body) => self.execute_subshell(body, context)?,
+            AstNode::Background(job) => self.execute_background(job, context)?,
+            AstNode::Command {
+                background: true, ..
+            } => self.execute_background(normalized_node, context)?,
             AstNode::Command {
                 name,
                 args,
                 redirections,
-                background,
+                ..
             } => {
-                // Handle command execution with background support
-                #[cfg(debug_assertions)]
-                {
-                    #[cfg(feature = "debug_exec")]
-                    eprintln!("DEBUG_EXEC: Command background flag = {background}");
+                let debug_trap = self.run_trap(TrapCondition::Debug, context)?;
+                let mut command_result =
+                    self.execute_command_with_background(name, args, redirections, context)?;
+                if let Some(trap) = debug_trap {
+                    command_result.stdout.insert_str(0, &trap.stdout);
+                    command_result.stderr.insert_str(0, &trap.stderr);
                 }
-                self.execute_command_with_background(
-                    name,
-                    args,
-                    redirections,
-                    *background,
-                    context,
-                )?
+                command_result
             }
             AstNode::Pipeline { elements, .. } => self.execute_pipeline(elements, context)?,
             AstNode::If {
@@ -1761,18 +1747,84 @@ impl Executor {
                 // Simplified For loop execution
                 self.execute_ast_direct(body, context)?
             }
+            AstNode::While { condition, body } => {
+                self.execute_loop(condition, body, false, context)?
+            }
+            AstNode::Until { condition, body } => {
+                self.execute_loop(condition, body, true, context)?
+            }
+            AstNode::ForC {
+                init,
+                condition,
+                update,
+                body,
+            } => self.execute_arith_for(
+                init.as_deref(),
+                condition.as_deref(),
+                update.as_deref(),
+                body,
+                context,
+            )?,
+            AstNode::Select {
+                variable,
+                options,
+                body,
+            } => self.execute_select(variable, options.as_deref(), body, context)?,
+            AstNode::TestExpression { .. } => {
+                // [[ ... ]]: status 0/1, or 2 for malformed expressions (bad regex, ...)
+                match crate::test_expr::evaluate(normalized_node, context) {
+                    Ok(true) => ExecutionResult::success(0),
+                    Ok(false) => ExecutionResult::failure(1),
+                    Err(e) => {
+                        ExecutionResult::failure(2).with_error(format!("nxsh: {e}\n").into_bytes())
+                    }
+                }
+            }
+            AstNode::ArithmeticExpansion { .. } => {
+                // Standalone ((expr)): status 0 when the value is non-zero
+                if Self::eval_arith_node(normalized_node, context)? != 0 {
+                    ExecutionResult::success(0)
+                } else {
+                    ExecutionResult::failure(1)
+                }
+            }
             AstNode::VariableAssignment {
                 name,
                 value,
-                operator: _,
+                operator,
                 is_local: _,
                 is_export: _,
                 is_readonly: _,
             } => {
-                // Handle variable assignment
-                let value_result = self.execute_ast_direct(value, context)?;
-                context.set_var(name.to_string(), value_result.stdout.trim().to_string());
-                ExecutionResult::success(0)
+                let (value, status) = match value.as_ref() {
+                    AstNode::Word(text) => {
+                        let (text, status) = self.splice_substitutions(text, context)?;
+                        (Self::expand_value_text(&text, context)?, status)
+                    }
+                    other => (
+                        self.execute_ast_direct(other, context)?
+                            .stdout
+                            .trim()
+                            .to_string(),
+                        None,
+                    ),
+                };
+                let append = matches!(operator, nxsh_parser::ast::AssignmentOperator::AddAssign);
+                let mut result = Self::assign_scalar(name, value, append, context);
+                // `x=$(false)` reports the substitution's status
+                if let Some(status) = status.filter(|_| result.exit_code == 0) {
+                    result.exit_code = status;
+                }
+                result
+            }
+            AstNode::ArrayAssignment {
+                name,
+                operator,
+                elements,
+                ..
+            } => {
+                let append = matches!(operator, nxsh_parser::ast::AssignmentOperator::AddAssign);
+                self.assign_array(name, elements, append, context)?
             }
             AstNode::StringLiteral { value, .. } => {
                 ExecutionResult::success(0).with_output(value.as_bytes().to_vec())
@@ -1783,8 +1835,8 @@ impl Executor {
             AstNode::Word(word) => {
                 ExecutionResult::success(0).with_output(word.as_bytes().to_vec())
             }
-            AstNode::VariableExpansion { name, .. } => {
-                let value = context.get_var(name).unwrap_or_default();
+            AstNode::VariableExpansion { name, modifier } => {
+                let value = Self::expand_variable(&Self::parameter_expr(name, modifier), context)?;
                 ExecutionResult::success(0).with_output(value.as_bytes().to_vec())
             }
             AstNode::MacroDeclaration { name, params, body } => {
@@ -2159,10 +2211,10 @@ impl Executor {
                             if consumed_len > 0 {
                                 body_start_src = &src[consumed_len.min(src.len())..];
                             }
-                            // 旧値保存 (環境変数ベース)
-                            let mut saved: Vec<(String, Option<String>)> = Vec::new();
+                            // 引数は新しい呼び出しフレームのローカル変数
+                            context.push_call_frame(&callee);
                             for name in &param_names {
-                                saved.push((name.clone(), context.get_var(name)));
+                                context.make_local(name);
                             }
                             // 引数バインド
                             let mut arg_idx = 0usize;
@@ -2196,29 +2248,25 @@ impl Executor {
                             }
                             // 実行 (空ボディなら no-op)
                             if body_start_src.trim().is_empty() {
-                                for (name, old) in saved {
-                                    match old {
-                                        Some(v) => context.set_var(name, v),
-                                        None => context.set_var(name, String::new()),
-                                    }
-                                }
+                                context.pop_call_frame();
                                 ExecutionResult::success(0)
                             } else {
                                 match parse_program(body_start_src) {
                                     Ok(ast) => {
-                                        let exec_res = self.execute_ast_direct(&ast, context);
+                                        let saved_positional =
+                                            context.set_positional_args(evaluated_args.clone());
+                                        let exec_res = self.execute_function_body(&ast, context);
+                                        context.set_positional_args(saved_positional);
                                         // スコープ復元
-                                        for (name, old) in saved {
-                                            match old {
-                                                Some(v) => context.set_var(name, v),
-                                                None => context.set_var(name, String::new()),
-                                            }
-                                        }
+                                        context.pop_call_frame();
                                         return exec_res;
                                     }
-                                    Err(_) => ExecutionResult::failure(1).with_error(
-                                        format!("function parse failed: {callee}").into_bytes(),
-                                    ),
+                                    Err(_) => {
+                                        context.pop_call_frame();
+                                        ExecutionResult::failure(1).with_error(
+                                            format!("function parse failed: {callee}").into_bytes(),
+                                        )
+                                    }
                                 }
                             }
                         } else {
@@ -2251,6 +2299,26 @@ impl Executor {
             }
         };
 
+        let mut result = result;
+        let is_simple = matches!(
+            normalized_node,
+            AstNode::Command { .. } | AstNode::Pipeline { .. }
+        );
+        if is_simple {
+            context.set_exit_status(result.exit_code);
+        }
+        if result.exit_code != 0 && self.condition_depth == 0 && is_simple {
+            let err_trap = self.run_trap(TrapCondition::Err, context)?;
+            Self::merge_trap_output(&mut result, err_trap);
+            if self.trap_depth == 0
+                && !self.return_pending
+                && context.get_option("errexit").unwrap_or(false)
+                && !context.continue_on_error()
+            {
+                self.errexit_pending = true;
+            }
+        }
+
         let execution_time = start_time.elapsed().as_micros() as u64;
 
         Ok(ExecutionResult {
@@ -2266,13 +2334,13 @@ impl Executor {
         })
     }
 
-    /// Execute command with background job support
+    /// Execute a foreground simple command (background ones go through
+    /// [`Self::execute_background`])
     fn execute_command_with_background(
         &mut self,
         name: &AstNode,
         args: &[AstNode],
-        _redirections: &[nxsh_parser::ast::Redirection],
-        background: bool,
+        redirections: &[nxsh_parser::ast::Redirection],
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
         let start_time = Instant::now();
@@ -2287,33 +2355,6 @@ impl Executor {
                 metrics: ExecutionMetrics::default(),
             });
         }
-        // Helper: split string into fields if NXSH_SUBST_SPLIT=1
-        fn split_fields(raw: &str, context: &ShellContext) -> Vec<String> {
-            if context.get_var("NXSH_SUBST_SPLIT").as_deref() != Some("1") {
-                return vec![raw.to_string()];
-            }
-            let ifs = context
-                .get_var("NXSH_IFS")
-                .unwrap_or_else(|| " \t\n".to_string());
-            let mut out = Vec::new();
-            let mut current = String::new();
-            for ch in raw.chars() {
-                if ifs.contains(ch) {
-                    if !current.is_empty() {
-                        out.push(std::mem::take(&mut current));
-                    }
-                } else {
-                    current.push(ch);
-                }
-            }
-            if !current.is_empty() {
-                out.push(current);
-            }
-            if out.is_empty() {
-                return vec![String::new()];
-            }
-            out
-        }
         // Extract command name
         let cmd_name = match name {
             AstNode::Word(word) => word.to_string(),
@@ -2327,42 +2368,150 @@ impl Executor {
         };
 
         // Extract & possibly split arguments
-        let mut cmd_args = Vec::new();
-        // Local brace expansion helper duplicated (cannot call inner fn in execute_command). Keep in sync.
-        fn brace_expand_one(input: &str) -> Vec<String> {
-            const MAX_EXPANSIONS: usize = 4096; // safety cap
-                                                // Escape handling via sentinels (same as expand_braces below)
-            const ESC_LBRACE: char = '\u{1F}';
-            const ESC_RBRACE: char = '\u{1E}';
-            const ESC_COMMA: char = '\u{1D}';
-            let mut transformed = String::with_capacity(input.len());
-            let mut it = input.chars().peekable();
-            while let Some(c) = it.next() {
-                if c == '\\' {
-                    if let Some(&next) = it.peek() {
-                        match next {
-                            '{' => {
-                                transformed.push(ESC_LBRACE);
-                                it.next();
-                                continue;
-                            }
-                            '}' => {
-                                transformed.push(ESC_RBRACE);
-                                it.next();
-                                continue;
-                            }
-                            ',' => {
-                                transformed.push(ESC_COMMA);
-                                it.next();
-                                continue;
-                            }
-                            _ => {
-                                transformed.push(next);
-                                it.next();
-                                continue;
-                            }
-                        }
-                    }
+        let cmd_args = self.expand_command_args(args, context)?;
+
+        if context.is_timed_out() {
+            return Ok(ExecutionResult {
+                exit_code: 124,
+                stdout: String::new(),
+                stderr: "nxsh: execution timed out".to_string(),
+                execution_time: start_time.elapsed().as_micros() as u64,
+                strategy: ExecutionStrategy::DirectInterpreter,
+                metrics: ExecutionMetrics::default(),
+            });
+        }
+        if context.get_option("xtrace").unwrap_or(false) {
+            use std::io::Write;
+            let trace = Self::format_trace(&cmd_name, &cmd_args, context);
+            let _ = context.stderr.write_all(trace.as_bytes());
+            let _ = context.stderr.flush();
+        }
+        let cmd_name = self
+            .offer_correction(&cmd_name, context)
+            .unwrap_or(cmd_name);
+        // Foreground builtin execution
+        // First, check user-defined shell functions registry
+        if context.has_function(&cmd_name) {
+            return self.execute_user_function_by_name(&cmd_name, &cmd_args, context);
+        }
+        match cmd_name.as_str() {
+            "source" | "." => return self.execute_source(&cmd_name, &cmd_args, context),
+            "return" => return self.execute_return(&cmd_args, context),
+            "exec" => return self.execute_exec(&cmd_args, redirections, context),
+            "timeout" => return self.execute_timeout(&cmd_args, context),
+            "nice" => return self.execute_nice(&cmd_args, context),
+            "watchfs" => return self.execute_watchfs(&cmd_args, context),
+            "fc" => return self.execute_fc(&cmd_args, context),
+            "profile" => return self.execute_profile(&cmd_args, context),
+            _ => {}
+        }
+        if context.is_timed_out() {
+            return Ok(ExecutionResult {
+                exit_code: 124,
+                stdout: String::new(),
+                stderr: "nxsh: execution timed out".to_string(),
+                execution_time: start_time.elapsed().as_micros() as u64,
+                strategy: ExecutionStrategy::DirectInterpreter,
+                metrics: ExecutionMetrics::default(),
+            });
+        }
+        if let Some(builtin) = self.builtins.get(&cmd_name) {
+            let r = builtin.execute(context, &cmd_args);
+            if context.is_timed_out() {
+                return Ok(ExecutionResult {
+                    exit_code: 124,
+                    stdout: String::new(),
+                    stderr: "nxsh: execution timed out".to_string(),
+                    execution_time: start_time.elapsed().as_micros() as u64,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics::default(),
+                });
+            }
+            return r;
+        }
+        // Then commands of loaded plugins, before `$PATH`
+        if let Some(command) = context.plugin_commands.get(&cmd_name) {
+            let r = command.execute(context, &cmd_args);
+            if context.is_timed_out() {
+                return Ok(ExecutionResult {
+                    exit_code: 124,
+                    stdout: String::new(),
+                    stderr: "nxsh: execution timed out".to_string(),
+                    execution_time: start_time.elapsed().as_micros() as u64,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics::default(),
+                });
+            }
+            return r;
+        }
+
+        // Execute as external command
+        if context.is_timed_out() {
+            return Ok(ExecutionResult {
+                exit_code: 124,
+                stdout: String::new(),
+                stderr: "nxsh: execution timed out".to_string(),
+                execution_time: start_time.elapsed().as_micros() as u64,
+                strategy: ExecutionStrategy::DirectInterpreter,
+                metrics: ExecutionMetrics::default(),
+            });
+        }
+        let r = self.execute_external_process(&cmd_name, &cmd_args, context);
+        if context.is_timed_out() {
+            return Ok(ExecutionResult {
+                exit_code: 124,
+                stdout: String::new(),
+                stderr: "nxsh: execution timed out".to_string(),
+                execution_time: start_time.elapsed().as_micros() as u64,
+                strategy: ExecutionStrategy::DirectInterpreter,
+                metrics: ExecutionMetrics::default(),
+            });
+        }
+        r
+    }
+
+    /// Expand a simple command's argument words into fields
+    fn expand_command_args(
+        &mut self,
+        args: &[AstNode],
+        context: &mut ShellContext,
+    ) -> ShellResult<Vec<String>> {
+        let mut cmd_args = Vec::new();
+        // Local brace expansion helper duplicated (cannot call inner fn in execute_command). Keep in sync.
+        fn brace_expand_one(input: &str) -> Vec<String> {
+            const MAX_EXPANSIONS: usize = 4096; // safety cap
+                                                // Escape handling via sentinels (same as expand_braces below)
+            const ESC_LBRACE: char = '\u{1F}';
+            const ESC_RBRACE: char = '\u{1E}';
+            const ESC_COMMA: char = '\u{1D}';
+            let mut transformed = String::with_capacity(input.len());
+            let mut it = input.chars().peekable();
+            while let Some(c) = it.next() {
+                if c == '\\' {
+                    if let Some(&next) = it.peek() {
+                        match next {
+                            '{' => {
+                                transformed.push(ESC_LBRACE);
+                                it.next();
+                                continue;
+                            }
+                            '}' => {
+                                transformed.push(ESC_RBRACE);
+                                it.next();
+                                continue;
+                            }
+                            ',' => {
+                                transformed.push(ESC_COMMA);
+                                it.next();
+                                continue;
+                            }
+                            _ => {
+                                transformed.push(next);
+                                it.next();
+                                continue;
+                            }
+                        }
+                    }
                     // trailing backslash
                     transformed.push('\\');
                 } else {
@@ -2571,6 +2720,9 @@ impl Executor {
         }
         for arg in args {
             match arg {
+                AstNode::Word(word) if word.contains("$(") || word.contains('`') => {
+                    cmd_args.push(self.substituted_word(word, context)?);
+                }
                 AstNode::Word(word) => {
                     let mut expanded = brace_expand_one(word);
                     let mut final_args = Vec::new();
@@ -2602,112 +2754,21 @@ impl Executor {
                     }
                 }
                 AstNode::NumberLiteral { value, .. } => cmd_args.push(value.to_string()),
-                AstNode::VariableExpansion { name, .. } => {
-                    cmd_args.push(context.get_var(name).unwrap_or_default());
+                AstNode::VariableExpansion { name, modifier } => {
+                    // `$@` and `${a[@]}` yield one field per element
+                    let expr = Self::parameter_expr(name, modifier);
+                    cmd_args.extend(Self::expand_variable_fields(&expr, context)?);
+                }
+                AstNode::VariableAssignment { .. } | AstNode::ArrayAssignment { .. } => {
+                    cmd_args.push(self.assignment_word(arg, context)?);
                 }
                 AstNode::CommandSubstitution { command, is_legacy } => {
-                    // Execute nested command substitution fully (use cache)
-                    let res = self.eval_cmd_substitution(command, context);
-                    match res {
-                        Ok(r) => {
-                            let mut merged = r.stdout;
-                            // stderr handling: NXSH_SUBST_STDERR=merge|separate (default: separate)
-                            let stderr_mode = context
-                                .get_var("NXSH_SUBST_STDERR")
-                                .unwrap_or_else(|| "separate".to_string());
-                            if stderr_mode.eq_ignore_ascii_case("merge") && !r.stderr.is_empty() {
-                                if !merged.is_empty() && !merged.ends_with('\n') {
-                                    merged.push('\n');
-                                }
-                                merged.push_str(&r.stderr);
-                            }
-                            let trimmed = merged.trim_end();
-                            // Legacy backticks behave like unquoted words; apply field splitting when opt-in or legacy
-                            let should_split = (*is_legacy)
-                                || context.get_var("NXSH_SUBST_SPLIT").as_deref() == Some("1");
-                            if should_split {
-                                for part in split_fields(trimmed, context) {
-                                    cmd_args.push(part);
-                                }
-                            } else {
-                                cmd_args.push(trimmed.to_string());
-                            }
-                        }
-                        Err(_) => cmd_args.push(String::new()),
-                    }
+                    cmd_args.extend(self.substitution_fields(command, *is_legacy, context));
                 }
                 _ => cmd_args.push(format!("{arg:?}")),
             }
         }
-
-        // Background execution takes precedence (even for builtins) so they behave like external jobs
-        if context.is_timed_out() {
-            return Ok(ExecutionResult {
-                exit_code: 124,
-                stdout: String::new(),
-                stderr: "nxsh: execution timed out".to_string(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
-        }
-        if background {
-            return self.execute_background_command(&cmd_name, cmd_args, context);
-        }
-
-        // Foreground builtin execution
-        // First, check user-defined shell functions registry
-        if context.has_function(&cmd_name) {
-            return self.execute_user_function_by_name(&cmd_name, &cmd_args, context);
-        }
-        if context.is_timed_out() {
-            return Ok(ExecutionResult {
-                exit_code: 124,
-                stdout: String::new(),
-                stderr: "nxsh: execution timed out".to_string(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
-        }
-        if let Some(builtin) = self.builtins.get(&cmd_name) {
-            let r = builtin.execute(context, &cmd_args);
-            if context.is_timed_out() {
-                return Ok(ExecutionResult {
-                    exit_code: 124,
-                    stdout: String::new(),
-                    stderr: "nxsh: execution timed out".to_string(),
-                    execution_time: start_time.elapsed().as_micros() as u64,
-                    strategy: ExecutionStrategy::DirectInterpreter,
-                    metrics: ExecutionMetrics::default(),
-                });
-            }
-            return r;
-        }
-
-        // Execute as external command
-        if context.is_timed_out() {
-            return Ok(ExecutionResult {
-                exit_code: 124,
-                stdout: String::new(),
-                stderr: "nxsh: execution timed out".to_string(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
-        }
-        let r = self.execute_external_process(&cmd_name, &cmd_args, context);
-        if context.is_timed_out() {
-            return Ok(ExecutionResult {
-                exit_code: 124,
-                stdout: String::new(),
-                stderr: "nxsh: execution timed out".to_string(),
-                execution_time: start_time.elapsed().as_micros() as u64,
-                strategy: ExecutionStrategy::DirectInterpreter,
-                metrics: ExecutionMetrics::default(),
-            });
-        }
-        r
+        Ok(cmd_args)
     }
 
     /// Execute a user-defined shell function stored in `ShellContext.functions`
@@ -2790,10 +2851,10 @@ impl Executor {
                 body_start_src = &src[consumed_len.min(src.len())..];
             }
 
-            // Save old and bind new variables
-            let mut saved: Vec<(String, Option<String>)> = Vec::new();
+            // Named parameters are locals of the new call frame
+            context.push_call_frame(func_name);
             for name in &param_names {
-                saved.push((name.clone(), context.get_var(name)));
+                context.make_local(name);
             }
             let mut arg_idx = 0usize;
             for (i, name) in param_names.iter().enumerate() {
@@ -2821,854 +2882,3473 @@ impl Executor {
                 }
             }
 
+            // `$1..$N` inside the body are the call's arguments
+            let saved_positional = context.set_positional_args(evaluated_args.to_vec());
+
             // Execute body (empty body is success)
             let result = if body_start_src.trim().is_empty() {
                 Ok(ExecutionResult::success(0))
             } else {
                 match parse_program(body_start_src) {
-                    Ok(ast) => self.execute_ast_direct(&ast, context),
+                    Ok(ast) => self.execute_function_body(&ast, context),
                     Err(_) => Ok(ExecutionResult::failure(1)
                         .with_error(format!("function parse failed: {func_name}").into_bytes())),
                 }
             };
-            // Restore variables
-            for (name, old) in saved {
-                match old {
-                    Some(v) => context.set_var(name, v),
-                    None => context.set_var(name, String::new()),
-                }
-            }
+            context.set_positional_args(saved_positional);
+            context.pop_call_frame();
             return result;
         }
         Ok(ExecutionResult::failure(1)
             .with_error(format!("missing function body: {func_name}").into_bytes()))
     }
 
-    /// Execute command in background
-    fn execute_background_command(
+    /// Run a function body in the call frame set up by the caller; `return`
+    /// leaves this call only
+    fn execute_function_body(
         &mut self,
-        command: &str,
-        args: Vec<String>,
+        body: &AstNode,
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        // Get job manager from context
+        self.return_depth += 1;
+        let result = self.execute_ast_direct(body, context);
+        self.return_depth -= 1;
+        self.return_pending = false;
+        result
+    }
+
+    /// Start `node` as a background job; the shell continues at once with
+    /// status 0.
+    ///
+    /// External commands, and pipelines made only of them, are spawned through
+    /// the job manager and run concurrently in their own process group.
+    /// Anything the shell has to run itself (builtins, functions, compound
+    /// commands) runs to completion in a forked context and is recorded as a
+    /// job that has already finished.
+    fn execute_background(
+        &mut self,
+        node: &AstNode,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        // A backgrounded simple command carries the flag itself; inside the
+        // job it runs like any foreground command
+        let unflagged;
+        let node = match node {
+            AstNode::Command {
+                name,
+                args,
+                redirections,
+                background: true,
+            } => {
+                unflagged = AstNode::Command {
+                    name: name.clone(),
+                    args: args.clone(),
+                    redirections: redirections.clone(),
+                    background: false,
+                };
+                &unflagged
+            }
+            other => other,
+        };
+        let stages: Vec<&AstNode> = match node {
+            AstNode::Pipeline { elements, .. } => elements.iter().collect(),
+            AstNode::JobGroup(members) => members.iter().collect(),
+            single => vec![single],
+        };
+        let names: Option<Vec<String>> = stages
+            .iter()
+            .map(|stage| self.external_name(stage, context))
+            .collect();
+
+        let description = node.to_string();
         let job_manager = context.job_manager();
-        let mut job_manager_guard = job_manager.lock().map_err(|_| {
+        let lock_poisoned = || {
             ShellError::new(
                 ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
                 "Job manager lock poisoned".to_string(),
             )
-        })?;
+        };
+        let mut result = ExecutionResult::success(0);
+        let job = match names {
+            Some(names) => {
+                let mut commands = Vec::with_capacity(names.len());
+                for (stage, name) in stages.iter().zip(&names) {
+                    let args = match stage {
+                        AstNode::Command { args, .. } => self.expand_command_args(args, context)?,
+                        _ => Vec::new(),
+                    };
+                    let mut command = Self::external_command(name, &args, context);
+                    self.schedule(&mut command);
+                    commands.push(command);
+                }
+                let mut manager = job_manager.lock().map_err(|_| lock_poisoned())?;
+                let job_id = if matches!(node, AstNode::JobGroup(_)) {
+                    manager.spawn_background_group(description, commands)?
+                } else {
+                    manager.spawn_background_pipeline(description, commands)?
+                };
+                manager.get_job(job_id)?
+            }
+            None => {
+                let finished = self.execute_subshell(node, context)?;
+                result.stdout = finished.stdout;
+                result.stderr = finished.stderr;
+                let mut manager = job_manager.lock().map_err(|_| lock_poisoned())?;
+                let job_id = manager.record_finished_job(description, finished.exit_code)?;
+                manager.get_job(job_id)?
+            }
+        };
 
-        // Spawn background job
-        let job_id = job_manager_guard.spawn_background_job(command.to_string(), args)?;
+        if let Some(job) = job {
+            for process in &job.processes {
+                if let JobStatus::Failed(message) = &process.status {
+                    result.stderr.push_str(&format!("nxsh: {message}\n"));
+                }
+            }
+            // `$!` is the last process of the pipeline or group
+            let pid = job
+                .processes
+                .iter()
+                .rev()
+                .map(|p| p.pid)
+                .find(|&pid| pid != 0);
+            if pid.is_some() {
+                context.last_background_pid = pid;
+            }
+            if context.is_interactive() {
+                match pid {
+                    Some(pid) => result.stderr.push_str(&format!("[{}] {pid}\n", job.id)),
+                    None => result.stderr.push_str(&format!("[{}]\n", job.id)),
+                }
+            }
+        }
+        context.set_exit_status(0);
+        Ok(result)
+    }
 
-        // Return immediately with job information
-        let output = format!("[{job_id}] Background job started: {command}");
-        println!("{output}"); // Also print to console
+    /// Name of a simple command that runs as an external program, or `None`
+    /// when the shell has to execute the node itself
+    fn external_name(&self, node: &AstNode, context: &ShellContext) -> Option<String> {
+        let AstNode::Command { name, .. } = node else {
+            return None;
+        };
+        let name = match name.as_ref() {
+            AstNode::Word(word) => word.to_string(),
+            AstNode::StringLiteral { value, .. } => value.to_string(),
+            _ => return None,
+        };
+        let in_shell = name.contains(['$', '`', '='])
+            || context.has_function(&name)
+            || context.get_alias(&name).is_some()
+            || self.builtins.contains_key(&name)
+            || context.plugin_commands.contains(&name)
+            || Self::SHELL_COMMANDS.contains(&name.as_str());
+        (!in_shell).then_some(name)
+    }
 
-        Ok(ExecutionResult::success(0).with_output(output.as_bytes().to_vec()))
+    /// Commands the executor runs itself because they evaluate code in, or
+    /// unwind, the caller's context
+    const SHELL_COMMANDS: &'static [&'static str] = &[
+        "source", ".", "return", "exec", "fc", "profile", "timeout", "nice", "watchfs",
+    ];
+
+    /// Builtins, plugin commands, functions, aliases and `$PATH` commands
+    /// close enough to `name` to be what was meant, closest first. Names of
+    /// up to three characters allow one edit, longer names two.
+    fn command_suggestions(&self, name: &str, context: &ShellContext) -> Vec<String> {
+        let max_distance = if name.chars().count() <= 3 { 1 } else { 2 };
+        let mut names: BTreeSet<String> = self.builtins.keys().cloned().collect();
+        names.extend(Self::SHELL_COMMANDS.iter().map(|name| name.to_string()));
+        names.extend(context.plugin_commands.names());
+        if let Ok(functions) = context.functions.read() {
+            names.extend(functions.keys().cloned());
+        }
+        if let Ok(aliases) = context.aliases.read() {
+            names.extend(aliases.keys().cloned());
+        }
+        let path = context.get_var("PATH").unwrap_or_default();
+        if let Ok(mut hash) = context.command_hash.lock() {
+            names.extend(hash.executables(std::ffi::OsStr::new(&path)));
+        }
+        let mut matches = nxsh_hal::command::closest_matches(
+            name,
+            names.iter().map(String::as_str),
+            max_distance,
+        );
+        matches.truncate(3);
+        matches
     }
 
-    /// Execute external process
-    fn execute_external_process(
-        &self,
-        command: &str,
-        args: &[String],
-        context: &ShellContext,
-    ) -> ShellResult<ExecutionResult> {
-        use std::io::ErrorKind as IoErrorKind;
-        use std::process::Command;
-        use wait_timeout::ChildExt;
+    /// `command not found` message for `name`, naming close matches
+    fn not_found_message(&self, name: &str, context: &ShellContext) -> String {
+        let suggestions = self.command_suggestions(name, context);
+        if suggestions.is_empty() {
+            format!("nxsh: command not found: {name}\n")
+        } else {
+            format!(
+                "nxsh: command not found: {name} — did you mean: {}?\n",
+                suggestions.join(", ")
+            )
+        }
+    }
 
-        let start_time = Instant::now();
+    /// With `shopt -s correct`, ask whether to run the closest match when
+    /// `name` is not a command. Only asked when a terminal can answer, and
+    /// never for aliases, which were expanded before this point.
+    fn offer_correction(&self, name: &str, context: &ShellContext) -> Option<String> {
+        use std::io::{BufRead, IsTerminal, Write};
 
-        let mut direct_cmd = Command::new(command);
-        direct_cmd.args(args);
-        if let Ok(env) = context.env.read() {
-            for (k, v) in env.iter() {
-                direct_cmd.env(k, v);
+        if !context.get_option("correct").unwrap_or(false)
+            || !self.output.is_inherit()
+            || !std::io::stdin().is_terminal()
+            || !std::io::stderr().is_terminal()
+        {
+            return None;
+        }
+        if name.contains('/')
+            || context.has_function(name)
+            || context.get_alias(name).is_some()
+            || self.builtins.contains_key(name)
+            || context.plugin_commands.contains(name)
+            || Self::SHELL_COMMANDS.contains(&name)
+        {
+            return None;
+        }
+        let path = context.get_var("PATH").unwrap_or_default();
+        if context
+            .command_hash
+            .lock()
+            .ok()?
+            .remember(name, std::ffi::OsStr::new(&path))
+            .is_some()
+        {
+            return None;
+        }
+        let candidate = self
+            .command_suggestions(name, context)
+            .into_iter()
+            .find(|candidate| context.get_alias(candidate).is_none())?;
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "nxsh: correct '{name}' to '{candidate}' [y/N]? ");
+        let _ = stderr.flush();
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer).ok()?;
+        matches!(answer.trim(), "y" | "Y" | "yes").then_some(candidate)
+    }
+
+    /// `source file [args...]` / `. file [args...]`: run a script in the
+    /// current context, so its variables, functions and `cd` persist. Extra
+    /// arguments become `$1..$N` while it runs; `return` leaves it early.
+    fn execute_source(
+        &mut self,
+        cmd_name: &str,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let Some(file) = args.first() else {
+            return Ok(ExecutionResult::failure(2).with_error(
+                format!(
+                    "nxsh: {cmd_name}: filename argument required\n\
+                     {cmd_name}: usage: {cmd_name} filename [arguments]\n"
+                )
+                .into_bytes(),
+            ));
+        };
+        let Some(path) = Self::find_source_file(file, context) else {
+            return Ok(ExecutionResult::failure(1)
+                .with_error(format!("nxsh: {file}: No such file or directory\n").into_bytes()));
+        };
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("nxsh: {file}: {e}\n").into_bytes()))
             }
+        };
+        if source.trim().is_empty() {
+            return Ok(ExecutionResult::success(0));
         }
-        direct_cmd.current_dir(&context.cwd);
+        let ast = match parse_program(&source) {
+            Ok(ast) => ast,
+            Err(e) => {
+                return Ok(ExecutionResult::failure(2)
+                    .with_error(format!("nxsh: {file}: {e}\n").into_bytes()))
+            }
+        };
 
-        #[cfg(windows)]
-        fn apply_common(cmd: &mut std::process::Command, ctx: &ShellContext) {
-            if let Ok(env) = ctx.env.read() {
-                for (k, v) in env.iter() {
-                    cmd.env(k, v);
+        let saved_positional =
+            (args.len() > 1).then(|| context.set_positional_args(args[1..].to_vec()));
+        self.return_depth += 1;
+        let result = self.execute_ast_direct(&ast, context);
+        self.return_depth -= 1;
+        self.return_pending = false;
+        if let Some(saved) = saved_positional {
+            context.set_positional_args(saved);
+        }
+        result
+    }
+
+    /// Resolve a `source` operand: names with a slash are paths relative to
+    /// the working directory; bare names are looked up in `$PATH` first
+    fn find_source_file(file: &str, context: &ShellContext) -> Option<PathBuf> {
+        if !file.contains('/') {
+            if let Some(path_var) = context.get_var("PATH") {
+                let found = std::env::split_paths(&path_var)
+                    .map(|dir| context.cwd.join(dir).join(file))
+                    .find(|candidate| candidate.is_file());
+                if found.is_some() {
+                    return found;
                 }
             }
-            cmd.current_dir(&ctx.cwd);
         }
+        let path = context.cwd.join(file);
+        path.is_file().then_some(path)
+    }
 
-        // Try to spawn (not .output()) so we can enforce timeout
-        let mut child = match direct_cmd.spawn() {
-            Ok(c) => c,
-            Err(e) => {
-                #[cfg(windows)]
-                {
-                    if e.kind() == IoErrorKind::NotFound {
-                        let lower = command.to_ascii_lowercase();
-                        let mut fb: Option<std::process::Command> = None;
-                        if lower == "echo" {
-                            // emulate echo via cmd
-                            let mut c = Command::new("cmd.exe");
-                            let mut full = String::from("echo");
-                            for a in args {
-                                full.push(' ');
-                                full.push_str(a);
-                            }
-                            c.args(["/C", &full]);
-                            fb = Some(c);
-                        } else if lower == "sleep" {
-                            // map to Start-Sleep
-                            let seconds = args
-                                .first()
-                                .and_then(|s| s.parse::<u64>().ok())
-                                .unwrap_or(1);
-                            let mut c = Command::new("powershell.exe");
-                            c.args([
-                                "-NoProfile",
-                                "-Command",
-                                &format!("Start-Sleep -Seconds {seconds}"),
-                            ]);
-                            fb = Some(c);
+    /// `return [n]`: leave the function or sourced file with status `n`, or
+    /// the status of the last command
+    fn execute_return(
+        &mut self,
+        args: &[String],
+        context: &ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        if self.return_depth == 0 {
+            return Ok(ExecutionResult::failure(1).with_error(
+                b"nxsh: return: can only `return' from a function or sourced script\n".to_vec(),
+            ));
+        }
+        let status = match args.first() {
+            None => context.get_exit_status(),
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(n) => (n & 0xff) as i32,
+                Err(_) => {
+                    self.return_pending = true;
+                    return Ok(ExecutionResult::failure(2).with_error(
+                        format!("nxsh: return: {arg}: numeric argument required\n").into_bytes(),
+                    ));
+                }
+            },
+        };
+        self.return_pending = true;
+        Ok(ExecutionResult::success(status))
+    }
+
+    /// `exec [-cl] [-a name] [command [args...]] [redirections]`: replace the
+    /// shell with `command`. Without a command the redirections rebind the
+    /// shell's own descriptors for the rest of the session (`exec 3<file`,
+    /// `exec >log 2>&1`, `exec 3>&-`).
+    fn execute_exec(
+        &mut self,
+        args: &[String],
+        redirections: &[nxsh_parser::ast::Redirection],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let mut clear_env = false;
+        let mut login = false;
+        let mut argv0 = None;
+        let mut rest = args;
+        while let Some(flag) = rest.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            rest = &rest[1..];
+            if flag == "--" {
+                break;
+            }
+            for c in flag[1..].chars() {
+                match c {
+                    'c' => clear_env = true,
+                    'l' => login = true,
+                    'a' => match rest.split_first() {
+                        Some((name, tail)) => {
+                            argv0 = Some(name.clone());
+                            rest = tail;
                         }
-                        if let Some(mut fb_cmd) = fb {
-                            apply_common(&mut fb_cmd, context);
-                            match fb_cmd.spawn() { Ok(c2)=> c2, Err(e2)=> return Err(ShellError::new(
-                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                                format!("Failed to execute command '{command}': {e} (fallback also failed: {e2})")
-                            )) }
-                        } else {
-                            return Err(ShellError::new(
-                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                                format!("Failed to execute command '{command}': {e}"),
-                            ));
+                        None => {
+                            return Ok(ExecutionResult::failure(2).with_error(
+                                b"nxsh: exec: -a: option requires an argument\n".to_vec(),
+                            ))
                         }
-                    } else {
-                        return Err(ShellError::new(
-                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                            format!("Failed to execute command '{command}': {e}"),
-                        ));
+                    },
+                    _ => {
+                        return Ok(ExecutionResult::failure(2).with_error(
+                            format!(
+                                "nxsh: exec: -{c}: invalid option\n\
+                                 exec: usage: exec [-cl] [-a name] [command [argument ...]] [redirection ...]\n"
+                            )
+                            .into_bytes(),
+                        ))
                     }
                 }
-                #[cfg(not(windows))]
-                {
-                    return Err(ShellError::new(
-                        ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                        format!("Failed to execute command '{command}': {e}"),
-                    ));
+            }
+        }
+
+        // A subshell, substitution or pipeline stage shares this process, so
+        // it cannot give the process away or rebind its descriptors
+        let forked = !self.output.is_inherit()
+            || self.input.is_some()
+            || context
+                .options
+                .read()
+                .map(|options| options.subshell_level > 0)
+                .unwrap_or(false);
+        if forked {
+            if !redirections.is_empty() {
+                return Ok(ExecutionResult::failure(1).with_error(
+                    b"nxsh: exec: redirections are not supported in a subshell\n".to_vec(),
+                ));
+            }
+            return match rest.split_first() {
+                Some((command, command_args)) => {
+                    let cmd =
+                        Self::exec_command(command, command_args, clear_env, login, argv0, context);
+                    self.execute_prepared_process(command, command_args, cmd, context)
                 }
+                None => Ok(ExecutionResult::success(0)),
+            };
+        }
+
+        {
+            use std::io::Write;
+            let _ = context.stdout.flush();
+            let _ = std::io::stdout().flush();
+            let _ = std::io::stderr().flush();
+        }
+        for redirection in redirections {
+            if let Err(message) = self.rebind_shell_fd(redirection, context) {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("nxsh: {message}\n").into_bytes()));
             }
+        }
+        let Some((command, command_args)) = rest.split_first() else {
+            return Ok(ExecutionResult::success(0));
         };
 
-        // Wait with optional per-command timeout
-        let output = if let Some(dur) = context.per_command_timeout() {
-            match child.wait_timeout(dur).map_err(|e| {
-                ShellError::new(
-                    ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                    format!("Process wait error: {e}"),
-                )
-            })? {
-                Some(_status) => child.wait_with_output().map_err(|e| {
-                    ShellError::new(
-                        ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                        format!("Process output error: {e}"),
-                    )
-                })?,
-                None => {
-                    // timeout
-                    let _ = child.kill();
-                    return Ok(ExecutionResult {
-                        exit_code: 124,
-                        stdout: String::new(),
-                        stderr: format!("nxsh: command '{command}' timed out"),
-                        execution_time: start_time.elapsed().as_micros() as u64,
-                        strategy: ExecutionStrategy::DirectInterpreter,
-                        metrics: ExecutionMetrics::default(),
-                    });
-                }
+        let mut cmd = Self::exec_command(command, command_args, clear_env, login, argv0, context);
+        if let Some(terminal) = &context.terminal {
+            let _ = terminal.reclaim();
+        }
+        let err = nxsh_hal::process::replace_process(&mut cmd);
+        let (exit_code, reason) = match err.kind() {
+            std::io::ErrorKind::NotFound => (127, "not found".to_string()),
+            std::io::ErrorKind::PermissionDenied => {
+                (126, "cannot execute: Permission denied".to_string())
             }
-        } else {
-            child.wait_with_output().map_err(|e| {
-                ShellError::new(
-                    ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                    format!("Process output error: {e}"),
-                )
-            })?
+            _ => (126, err.to_string()),
         };
+        Ok(ExecutionResult::failure(exit_code)
+            .with_error(format!("nxsh: exec: {command}: {reason}\n").into_bytes()))
+    }
 
-        let execution_time = start_time.elapsed().as_micros() as u64;
-        Ok(ExecutionResult {
-            exit_code: output.status.code().unwrap_or(-1),
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-            execution_time,
-            strategy: ExecutionStrategy::DirectInterpreter,
-            metrics: ExecutionMetrics {
-                compile_time_us: 0,
-                optimize_time_us: 0,
-                execute_time_us: execution_time,
-                instruction_count: 1,
-                memory_usage: (output.stdout.len() + output.stderr.len()) as u64,
-            },
-        })
+    /// Process command for the program `exec` runs: `-c` clears its
+    /// environment, `-a` renames it and `-l` prefixes that name with a dash
+    fn exec_command(
+        command: &str,
+        args: &[String],
+        clear_env: bool,
+        login: bool,
+        argv0: Option<String>,
+        context: &ShellContext,
+    ) -> std::process::Command {
+        let mut cmd = Self::external_command(command, args, context);
+        if clear_env {
+            cmd.env_clear();
+        }
+        #[cfg(unix)]
+        {
+            let name = argv0.unwrap_or_else(|| command.to_string());
+            let name = if login { format!("-{name}") } else { name };
+            std::os::unix::process::CommandExt::arg0(&mut cmd, name);
+        }
+        #[cfg(not(unix))]
+        let _ = (argv0, login);
+        cmd
     }
 
-    /// Execute a single command
-    #[allow(dead_code)]
-    fn execute_command(
+    /// Run a command with a time limit (`timeout`). A program started for it
+    /// gets the signal when the limit passes, and KILL after `-k` more;
+    /// builtins and functions stop at the next command they run. The status
+    /// is 124 when the limit passed, or the command's own with
+    /// `--preserve-status`.
+    fn execute_timeout(
         &mut self,
-        name: &str,
-        args: &[AstNode],
+        args: &[String],
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        // Pre-evaluate & possibly split arguments
-        fn split_fields(raw: &str, context: &ShellContext) -> Vec<String> {
-            if context.get_var("NXSH_SUBST_SPLIT").as_deref() != Some("1") {
-                return vec![raw.to_string()];
-            }
-            let ifs = context
-                .get_var("NXSH_IFS")
-                .unwrap_or_else(|| " \t\n".to_string());
-            let mut out = Vec::new();
-            let mut current = String::new();
-            for ch in raw.chars() {
-                if ifs.contains(ch) {
-                    if !current.is_empty() {
-                        out.push(std::mem::take(&mut current));
+        let usage = |message: String| {
+            Ok(ExecutionResult::failure(125).with_error(
+                format!(
+                    "nxsh: timeout: {message}\n\
+                     timeout: usage: timeout [-v] [-s signal] [-k duration] [--preserve-status] duration command [argument ...]\n"
+                )
+                .into_bytes(),
+            ))
+        };
+        let mut signal = JobSignal::Terminate;
+        let mut kill_after = None;
+        let mut preserve_status = false;
+        let mut verbose = false;
+        let mut rest = args;
+        while let Some(flag) = rest.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            rest = &rest[1..];
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+                _ if !flag.starts_with("--") && flag.len() > 2 => (&flag[..2], Some(&flag[2..])),
+                _ => (flag.as_str(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Some(value.to_string()),
+                None => rest.split_first().map(|(value, tail)| {
+                    rest = tail;
+                    value.clone()
+                }),
+            };
+            match name {
+                "--" => break,
+                "--preserve-status" => preserve_status = true,
+                "-v" | "--verbose" => verbose = true,
+                "-s" | "--signal" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    signal = match nxsh_hal::signal::parse_signal(&spec)
+                        .and_then(JobSignal::from_signal_number)
+                    {
+                        Some(signal) => signal,
+                        None => return usage(format!("{spec}: invalid signal")),
+                    };
+                }
+                "-k" | "--kill-after" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    match Self::parse_timeout_duration(&spec) {
+                        Some(duration) => kill_after = Some(duration),
+                        None => return usage(format!("{spec}: invalid time interval")),
                     }
-                } else {
-                    current.push(ch);
                 }
+                _ => return usage(format!("{flag}: invalid option")),
             }
-            if !current.is_empty() {
-                out.push(current);
+        }
+        let Some((limit, rest)) = rest.split_first() else {
+            return usage("missing operand".to_string());
+        };
+        let Some(limit) = Self::parse_timeout_duration(limit) else {
+            return usage(format!("{limit}: invalid time interval"));
+        };
+        let Some((command, command_args)) = rest.split_first() else {
+            return usage("missing command".to_string());
+        };
+
+        // A duration of 0 sets no limit, and an enclosing `timeout` that
+        // ends sooner still applies
+        let deadline = (!limit.is_zero()).then(|| Deadline {
+            at: Instant::now() + limit,
+            signal,
+            kill_after,
+            verbose,
+        });
+        let deadline = deadline
+            .into_iter()
+            .chain(self.deadline)
+            .min_by_key(|d| d.at);
+        let outer = (self.deadline, context.global_deadline());
+        self.deadline = deadline;
+        if let Some(deadline) = deadline {
+            let at = outer.1.map_or(deadline.at, |at| at.min(deadline.at));
+            context.set_global_deadline(Some(at));
+        }
+        let result = self.execute_wrapped_command("timeout", command, command_args, context);
+        self.deadline = outer.0;
+        context.set_global_deadline(outer.1);
+
+        let mut result = result?;
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline.at) {
+            // The notice the executor gives commands the limit stopped is
+            // for the shell's own time limit
+            if let Some(stderr) = result.stderr.strip_suffix("nxsh: execution timed out") {
+                result.stderr = stderr.to_string();
             }
-            if out.is_empty() {
-                vec![String::new()]
-            } else {
-                out
+            if !preserve_status {
+                result.exit_code = 124;
             }
         }
-        // Advanced (yet bounded) brace expansion supporting:
-        //  - comma lists: {a,b,c}
-        //  - nested lists: {a,{b,c}}
-        //  - numeric ranges: {1..3} => 1 2 3 ; with optional step {1..10..2}
-        //  - alpha ranges: {a..c} => a b c
-        // Limit expansions to avoid exponential blowup (MAX_EXPANSIONS)
-        fn expand_braces(input: &str) -> Vec<String> {
-            const MAX_EXPANSIONS: usize = 4096; // safety cap
-                                                // Provide escape handling: backslash preceding { } , prevents structural meaning.
-                                                // Strategy: temporarily replace escaped tokens with sentinel bytes, run normal logic, then restore.
-            const ESC_LBRACE: char = '\u{1F}';
-            const ESC_RBRACE: char = '\u{1E}';
-            const ESC_COMMA: char = '\u{1D}';
-            let mut transformed = String::with_capacity(input.len());
-            let mut chars = input.chars().peekable();
-            while let Some(c) = chars.next() {
-                if c == '\\' {
-                    // escape next char if brace related
-                    if let Some(&next) = chars.peek() {
-                        match next {
-                            '{' => {
-                                transformed.push(ESC_LBRACE);
-                                chars.next();
-                                continue;
-                            }
-                            '}' => {
-                                transformed.push(ESC_RBRACE);
-                                chars.next();
-                                continue;
-                            }
-                            ',' => {
-                                transformed.push(ESC_COMMA);
-                                chars.next();
-                                continue;
-                            }
-                            _ => {
-                                transformed.push(next);
-                                chars.next();
-                                continue;
-                            }
-                        }
-                    }
-                    // trailing backslash -> keep
-                    transformed.push('\\');
-                } else {
-                    transformed.push(c);
-                }
+        Ok(result)
+    }
+
+    /// Run the command `timeout` or `nice` was given, by the name it has in
+    /// the shell
+    fn execute_wrapped_command(
+        &mut self,
+        wrapper: &str,
+        command: &str,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        if context.has_function(command) {
+            return self.execute_user_function_by_name(command, args, context);
+        }
+        match command {
+            "source" | "." => return self.execute_source(command, args, context),
+            "timeout" => return self.execute_timeout(args, context),
+            "nice" => return self.execute_nice(args, context),
+            "watchfs" => return self.execute_watchfs(args, context),
+            "fc" => return self.execute_fc(args, context),
+            "profile" => return self.execute_profile(args, context),
+            "return" | "exec" => {
+                return Ok(ExecutionResult::failure(126).with_error(
+                    format!("nxsh: {wrapper}: {command}: must be run directly\n").into_bytes(),
+                ))
             }
-            // Fast path: if no '{' present return original (after restoration)
-            if !transformed.as_bytes().contains(&b'{') {
-                return vec![input.to_string()];
+            _ => {}
+        }
+        if let Some(builtin) = self.builtins.get(command).cloned() {
+            return builtin.execute(context, args);
+        }
+        if let Some(plugin) = context.plugin_commands.get(command) {
+            return plugin.execute(context, args);
+        }
+        self.execute_external_process(command, args, context)
+    }
+
+    /// A `timeout` duration: a number of seconds, or of minutes, hours or
+    /// days with an `m`, `h` or `d` suffix
+    fn parse_timeout_duration(spec: &str) -> Option<Duration> {
+        let (number, scale) = match spec.char_indices().last()? {
+            (i, 's') => (&spec[..i], 1.0),
+            (i, 'm') => (&spec[..i], 60.0),
+            (i, 'h') => (&spec[..i], 3600.0),
+            (i, 'd') => (&spec[..i], 86400.0),
+            _ => (spec, 1.0),
+        };
+        let seconds = number.parse::<f64>().ok()? * scale;
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    /// Wait for a program started under `deadline`, sending its process
+    /// group the deadline's signal once it passes and KILL after the grace
+    /// period. Notices of the signals sent are returned as its stderr, along
+    /// with whether it had to be signalled.
+    fn wait_for_deadline(
+        command: &str,
+        mut child: std::process::Child,
+        deadline: Deadline,
+        context: &ShellContext,
+    ) -> ShellResult<(std::process::Output, bool)> {
+        use std::io::Read;
+        use wait_timeout::ChildExt;
+
+        let wait_error = |e: std::io::Error| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Process wait error: {e}"),
+            )
+        };
+        // Drain captured output meanwhile, or a program that fills the pipe
+        // would only stop when it is killed
+        let reader = child.stdout.take().map(|mut stdout| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = stdout.read_to_end(&mut output);
+                output
+            })
+        });
+        let pid = child.id();
+        let mut notices = Vec::new();
+        let mut send = |signal: JobSignal| -> ShellResult<()> {
+            if deadline.verbose {
+                let name = nxsh_hal::signal::signal_name(signal.to_signal_number()).unwrap_or("?");
+                notices.extend_from_slice(
+                    format!("timeout: sending signal {name} to command '{command}'\n").as_bytes(),
+                );
             }
-            fn restore(s: String) -> String {
-                let mut out = String::with_capacity(s.len());
-                for c in s.chars() {
-                    match c {
-                        '\u{1F}' => {
-                            out.push('\\');
-                            out.push('{');
-                        }
-                        '\u{1E}' => {
-                            out.push('\\');
-                            out.push('}');
-                        }
-                        '\u{1D}' => {
-                            out.push('\\');
-                            out.push(',');
-                        }
-                        _ => out.push(c),
-                    }
+            // The program may exit just before the signal, which is no error
+            let _ = crate::builtins::lock_jobs(&context.job_manager())?
+                .send_signal_to_process_group(pid, signal);
+            Ok(())
+        };
+
+        // The wait can end a little early; the limit is only up once the
+        // clock says so
+        let mut status = None;
+        while status.is_none() && Instant::now() < deadline.at {
+            let remaining = deadline.at.saturating_duration_since(Instant::now());
+            status = child.wait_timeout(remaining).map_err(wait_error)?;
+        }
+        let timed_out = status.is_none();
+        if timed_out {
+            send(deadline.signal)?;
+            if let Some(grace) = deadline.kill_after {
+                status = child.wait_timeout(grace).map_err(wait_error)?;
+                if status.is_none() {
+                    send(JobSignal::Kill)?;
                 }
-                out
             }
-            // Find first top-level {...}
-            let bytes = transformed.as_bytes();
-            let mut level = 0usize;
-            let mut start_idx = None;
-            for (i, &b) in bytes.iter().enumerate() {
-                match b {
-                    b'{' => {
-                        if level == 0 {
-                            start_idx = Some(i);
-                        }
-                        level += 1;
+        }
+        let status = match status {
+            Some(status) => status,
+            None => child.wait().map_err(wait_error)?,
+        };
+        let output = std::process::Output {
+            status,
+            stdout: reader
+                .map(|reader| reader.join().unwrap_or_default())
+                .unwrap_or_default(),
+            stderr: notices,
+        };
+        Ok((output, timed_out))
+    }
+
+    /// Run a command at another scheduling priority (`nice`). The programs
+    /// it starts get the nice value `-n` adds to the current one, 10 by
+    /// default, and run only on the CPUs `--cpu-list` names; builtins and
+    /// functions still run in the shell itself. Without a command, print the
+    /// current nice value.
+    fn execute_nice(
+        &mut self,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use nxsh_hal::process::{NICE_MAX, NICE_MIN};
+
+        let usage = |message: String| {
+            Ok(ExecutionResult::failure(125).with_error(
+                format!(
+                    "nxsh: nice: {message}\n\
+                     nice: usage: nice [-n adjustment] [--cpu-list cpus] [command [argument ...]]\n"
+                )
+                .into_bytes(),
+            ))
+        };
+        let mut adjustment = None;
+        let mut cpus = None;
+        let mut rest = args;
+        while let Some(flag) = rest.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            rest = &rest[1..];
+            // `-5` is the historical spelling of `-n 5`, and `--5` of `-n -5`
+            if let Ok(n) = flag[1..].parse::<i32>() {
+                adjustment = Some(n);
+                continue;
+            }
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+                _ if !flag.starts_with("--") && flag.len() > 2 => (&flag[..2], Some(&flag[2..])),
+                _ => (flag.as_str(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Some(value.to_string()),
+                None => rest.split_first().map(|(value, tail)| {
+                    rest = tail;
+                    value.clone()
+                }),
+            };
+            match name {
+                "--" => break,
+                "-n" | "--adjustment" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    match spec.parse::<i32>() {
+                        Ok(n) => adjustment = Some(n),
+                        Err(_) => return usage(format!("{spec}: invalid adjustment")),
                     }
-                    b'}' => {
-                        if level > 0 {
-                            level -= 1;
-                            if level == 0 {
-                                // complete group
-                                let open = start_idx.unwrap();
-                                let inner = &transformed[open + 1..i];
-                                let prefix = &transformed[..open];
-                                let suffix = &transformed[i + 1..];
-                                // Determine if inner should expand: top-level comma or a valid range pattern
-                                let mut has_top_level_comma = false;
-                                {
-                                    let mut lvl = 0usize;
-                                    for ch in inner.chars() {
-                                        match ch {
-                                            '{' => lvl += 1,
-                                            '}' => {
-                                                if lvl > 0 {
-                                                    lvl = lvl.saturating_sub(1);
-                                                }
-                                            }
-                                            ',' if lvl == 0 => {
-                                                has_top_level_comma = true;
-                                                break;
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                }
-                                let variants = if has_top_level_comma || try_range(inner).is_some()
-                                {
-                                    parse_brace_inner(inner)
-                                } else {
-                                    // Not expandable: keep literal braces
-                                    vec![format!("{{{}}}", inner)]
-                                };
-                                let suffix_expanded = expand_braces(suffix);
-                                let mut out = Vec::new();
-                                for v in variants {
-                                    let v_expanded = expand_braces(&v);
-                                    for ve in v_expanded {
-                                        for tail in &suffix_expanded {
-                                            out.push(restore(format!("{prefix}{ve}{tail}")));
-                                            if out.len() >= MAX_EXPANSIONS {
-                                                // mark truncation via env var for diagnostics
-                                                std::env::set_var(
-                                                    "NXSH_BRACE_EXPANSION_TRUNCATED",
-                                                    "1",
-                                                );
-                                                return out;
-                                            }
-                                        }
-                                    }
-                                }
-                                return out;
-                            }
-                        }
+                }
+                "-c" | "--cpu-list" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    match Self::parse_cpu_list(&spec) {
+                        Some(list) => cpus = Some(list),
+                        None => return usage(format!("{spec}: invalid CPU list")),
                     }
-                    _ => {}
                 }
+                _ => return usage(format!("{flag}: invalid option")),
             }
-            vec![input.to_string()] // no complete group
         }
 
-        fn parse_brace_inner(inner: &str) -> Vec<String> {
-            // Detect range patterns first: {start..end[..step]}
-            if let Some(range_variants) = try_range(inner) {
-                return range_variants;
+        // Under an enclosing `nice`, adjust what it set
+        let current = match self.scheduling.as_ref().and_then(|s| s.nice) {
+            Some(nice) => nice,
+            None => nxsh_hal::process::priority(0).unwrap_or(0),
+        };
+        let Some((command, command_args)) = rest.split_first() else {
+            if adjustment.is_some() || cpus.is_some() {
+                return usage("a command must be given with an adjustment".to_string());
             }
-            let mut parts = Vec::new();
-            let mut level = 0usize;
-            let mut escape = false;
-            let mut current = String::new();
-            for c in inner.chars() {
-                if escape {
-                    current.push(c);
-                    escape = false;
-                    continue;
-                }
-                match c {
-                    '\\' => {
-                        escape = true;
-                    }
-                    '{' => {
-                        level += 1;
-                        current.push(c);
+            return Ok(ExecutionResult::success(0).with_output(format!("{current}\n").into_bytes()));
+        };
+        let nice = current.saturating_add(adjustment.unwrap_or(10));
+        let scheduling = nxsh_hal::Scheduling {
+            nice: Some(nice.clamp(NICE_MIN, NICE_MAX)),
+            cpus: cpus.or_else(|| self.scheduling.as_ref().and_then(|s| s.cpus.clone())),
+        };
+        if let Err(e) = scheduling.check() {
+            let message = match e {
+                nxsh_hal::HalError::Invalid(message) => message,
+                other => other.to_string(),
+            };
+            return Ok(ExecutionResult::failure(125)
+                .with_error(format!("nxsh: nice: {message}\n").into_bytes()));
+        }
+
+        let outer = self.scheduling.replace(scheduling);
+        let result = self.execute_wrapped_command("nice", command, command_args, context);
+        self.scheduling = outer;
+        result
+    }
+
+    /// Run a command again whenever files change (`watchfs`). The paths are
+    /// watched with everything below them; `-g` keeps only the files that
+    /// match one of its patterns, by name or by path below the watched
+    /// directory, and `-i` drops those matching one of its own, besides
+    /// version control and editor backup files. The command runs once at
+    /// the start unless `-p` is given, then after each batch of changes,
+    /// with the changed files listed one per line in `WATCHFS_CHANGED`.
+    /// Watching ends after `-n` such runs, at the limit of an enclosing
+    /// `timeout`, or when a signal arrives.
+    fn execute_watchfs(
+        &mut self,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use crate::test_expr::glob_match;
+        use nxsh_hal::fs::FileChange;
+        use nxsh_hal::FsWatcher;
+
+        /// Files a rebuild is never wanted for
+        const DEFAULT_IGNORES: [&str; 5] = [".git/*", "*/.git/*", "*.swp", "*~", ".#*"];
+        /// How long to wait for changes before checking whether to stop
+        const SLICE: Duration = Duration::from_millis(100);
+
+        let usage = |message: String| {
+            Ok(ExecutionResult::failure(2).with_error(
+                format!(
+                    "nxsh: watchfs: {message}\n\
+                     watchfs: usage: watchfs [-p] [-n count] [-d delay] [-g glob] [-i glob] path ... -- command [argument ...]\n"
+                )
+                .into_bytes(),
+            ))
+        };
+        let mut globs = Vec::new();
+        let mut ignores = Vec::new();
+        let mut default_ignores = true;
+        let mut debounce = Duration::from_millis(100);
+        let mut count = None;
+        let mut postpone = false;
+        let mut rest = args;
+        while let Some(flag) = rest
+            .first()
+            .filter(|a| a.starts_with('-') && a.len() > 1 && *a != "--")
+        {
+            rest = &rest[1..];
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+                _ if !flag.starts_with("--") && flag.len() > 2 => (&flag[..2], Some(&flag[2..])),
+                _ => (flag.as_str(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Some(value.to_string()),
+                None => rest.split_first().map(|(value, tail)| {
+                    rest = tail;
+                    value.clone()
+                }),
+            };
+            match name {
+                "-p" | "--postpone" => postpone = true,
+                "--no-default-ignore" => default_ignores = false,
+                "-g" | "--glob" | "-i" | "--ignore" => {
+                    let Some(pattern) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    if matches!(name, "-g" | "--glob") {
+                        globs.push(pattern);
+                    } else {
+                        ignores.push(pattern);
                     }
-                    '}' => {
-                        if level > 0 {
-                            level = level.saturating_sub(1);
-                        }
-                        current.push(c);
+                }
+                "-d" | "--debounce" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    match Self::parse_timeout_duration(&spec) {
+                        Some(delay) => debounce = delay,
+                        None => return usage(format!("{spec}: invalid time interval")),
                     }
-                    ',' if level == 0 => {
-                        parts.push(current.to_string());
-                        current.clear();
+                }
+                "-n" | "--count" => {
+                    let Some(spec) = value() else {
+                        return usage(format!("{name}: option requires an argument"));
+                    };
+                    match spec.parse::<usize>() {
+                        Ok(n) if n > 0 => count = Some(n),
+                        _ => return usage(format!("{spec}: invalid count")),
                     }
-                    _ => current.push(c),
                 }
+                _ => return usage(format!("{flag}: invalid option")),
             }
-            // Allow trailing empty element {a,b,}
-            if escape {
-                current.push('\\');
-            }
-            parts.push(current.to_string());
-            parts
         }
+        if default_ignores {
+            ignores.extend(DEFAULT_IGNORES.iter().map(|pattern| pattern.to_string()));
+        }
+        let Some(separator) = rest.iter().position(|arg| arg == "--") else {
+            return usage("missing -- before the command".to_string());
+        };
+        let (paths, command) = (&rest[..separator], &rest[separator + 1..]);
+        if paths.is_empty() {
+            return usage("missing path".to_string());
+        }
+        let Some((command, command_args)) = command.split_first() else {
+            return usage("missing command".to_string());
+        };
 
-        fn try_range(inner: &str) -> Option<Vec<String>> {
-            // Numeric or alpha range like 1..5 or a..f or 1..10..2
-            let mut segs = inner.split("..").collect::<Vec<_>>();
-            if segs.len() < 2 {
-                return None;
+        let failure = |message: String| {
+            Ok(ExecutionResult::failure(1)
+                .with_error(format!("nxsh: watchfs: {message}\n").into_bytes()))
+        };
+        let mut watcher = match FsWatcher::new(debounce) {
+            Ok(watcher) => watcher,
+            Err(e) => return failure(e.to_string()),
+        };
+        // Rebuilt from the components, `.` is left out of the changed paths
+        let roots: Vec<PathBuf> = paths
+            .iter()
+            .map(|path| context.cwd.join(path).components().collect())
+            .collect();
+        for (root, path) in roots.iter().zip(paths) {
+            if !root.exists() {
+                return failure(format!("{path}: No such file or directory"));
             }
-            if segs.len() > 3 {
-                return None;
+            if let Err(e) = watcher.watch(root) {
+                return failure(format!("{path}: {e}"));
             }
-            let mut step_abs = if segs.len() == 3 {
-                segs.pop()?.parse::<i64>().ok()?
-            } else {
-                1
-            };
-            if step_abs == 0 {
-                return None;
+        }
+        let wanted = |path: &Path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let relative = roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .map_or_else(
+                    || name.clone(),
+                    |relative| relative.to_string_lossy().replace('\\', "/"),
+                );
+            let matches =
+                |pattern: &String| glob_match(pattern, &name) || glob_match(pattern, &relative);
+            (globs.is_empty() || globs.iter().any(matches)) && !ignores.iter().any(matches)
+        };
+
+        let mut output = (String::new(), String::new());
+        let mut status = 0;
+        if !postpone {
+            status = self.run_watched(command, command_args, &[], &mut output, context)?;
+        }
+        let mut runs = 0;
+        while count.is_none_or(|count| runs < count) {
+            if context.is_timed_out() || nxsh_hal::signal::has_pending() || self.unwinding() {
+                break;
             }
-            step_abs = step_abs.abs();
-            let end_str = segs.pop()?;
-            let start_str = segs.pop()?;
-            // numeric
-            if let (Ok(start), Ok(end)) = (start_str.parse::<i64>(), end_str.parse::<i64>()) {
-                let dir = if end >= start { 1 } else { -1 };
-                let step = step_abs * dir;
-                let mut out = Vec::new();
-                let mut v = start;
-                while (step > 0 && v <= end) || (step < 0 && v >= end) {
-                    out.push(v.to_string());
-                    v += step;
-                    if out.len() >= 2048 {
-                        break;
+            let changes = match watcher.wait(Some(SLICE)) {
+                Ok(changes) => changes,
+                Err(e) => return failure(e.to_string()),
+            };
+            let files: Vec<PathBuf> = changes
+                .iter()
+                .map(FileChange::path)
+                .filter(|path| wanted(path))
+                .map(Path::to_path_buf)
+                .collect();
+            if files.is_empty() {
+                continue;
+            }
+            status = self.run_watched(command, command_args, &files, &mut output, context)?;
+            runs += 1;
+        }
+        context.unset_var("WATCHFS_CHANGED");
+        Ok(ExecutionResult::success(status)
+            .with_output(output.0.into_bytes())
+            .with_error(output.1.into_bytes()))
+    }
+
+    /// One run of the command `watchfs` was given, for a change to `files`.
+    /// Output to the shell's own streams is written at once, as watching
+    /// may go on indefinitely; otherwise it is added to `output`.
+    fn run_watched(
+        &mut self,
+        command: &str,
+        args: &[String],
+        files: &[PathBuf],
+        output: &mut (String, String),
+        context: &mut ShellContext,
+    ) -> ShellResult<i32> {
+        use std::io::Write;
+
+        let changed: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+        context.set_var("WATCHFS_CHANGED", changed.join("\n"));
+        let result = self.execute_wrapped_command("watchfs", command, args, context)?;
+        if self.output.is_inherit() {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(result.stdout.as_bytes());
+            let _ = stdout.flush();
+            let mut stderr = std::io::stderr();
+            let _ = stderr.write_all(result.stderr.as_bytes());
+            let _ = stderr.flush();
+        } else {
+            output.0.push_str(&result.stdout);
+            output.1.push_str(&result.stderr);
+        }
+        Ok(result.exit_code)
+    }
+
+    /// CPU numbers given like `0,2,4-7`
+    fn parse_cpu_list(spec: &str) -> Option<Vec<usize>> {
+        let mut cpus = Vec::new();
+        for part in spec.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (first.parse::<usize>().ok()?, last.parse().ok()?);
+                    if first > last {
+                        return None;
                     }
+                    cpus.extend(first..=last);
                 }
-                return Some(out);
+                None => cpus.push(part.parse().ok()?),
             }
-            // alpha single char
-            if start_str.len() == 1 && end_str.len() == 1 {
-                let (a, b) = (
-                    start_str.chars().next().unwrap(),
-                    end_str.chars().next().unwrap(),
-                );
-                if !a.is_ascii_alphabetic() || !b.is_ascii_alphabetic() {
-                    return None;
-                }
-                let (ai, bi) = (a as i16, b as i16);
-                let dir: i16 = if bi >= ai { 1 } else { -1 };
-                let step: i16 = (step_abs as i16) * dir;
-                let mut out = Vec::new();
-                let mut cur = ai;
-                while (step > 0 && cur <= bi) || (step < 0 && cur >= bi) {
-                    out.push(char::from_u32(cur as u32).unwrap().to_string());
-                    cur += step;
-                    if out.len() >= 2048 {
-                        break;
-                    }
-                }
-                return Some(out);
+        }
+        Some(cpus)
+    }
+
+    /// Have the program `cmd` starts scheduled as the enclosing `nice` asked
+    fn schedule(&self, cmd: &mut std::process::Command) {
+        if let Some(scheduling) = &self.scheduling {
+            scheduling.prepare(cmd);
+        }
+    }
+
+    /// Finish scheduling a started program: Windows can pin it to the CPUs
+    /// `nice` named only once it runs
+    fn schedule_started(&self, pid: u32) {
+        #[cfg(windows)]
+        if let Some(scheduling) = &self.scheduling {
+            let _ = scheduling.apply(pid);
+        }
+        #[cfg(not(windows))]
+        let _ = pid;
+    }
+
+    /// Apply one of `exec`'s redirections to the shell process itself
+    fn rebind_shell_fd(
+        &mut self,
+        redirection: &nxsh_parser::ast::Redirection,
+        context: &mut ShellContext,
+    ) -> Result<(), String> {
+        use nxsh_hal::process::{rebind_fd, FdTarget};
+        use nxsh_parser::ast::{RedirectionOperator as Op, RedirectionTarget, RedirectionType};
+
+        let fd = redirection.fd.unwrap_or(match redirection.redir_type {
+            RedirectionType::Input | RedirectionType::InputOutput => 0,
+            RedirectionType::Error | RedirectionType::ErrorAppend => 2,
+            _ => 1,
+        });
+        let target = match &redirection.target {
+            RedirectionTarget::FileDescriptor(from) => FdTarget::Duplicate(*from),
+            RedirectionTarget::Close => FdTarget::Close,
+            RedirectionTarget::HereDoc { .. } => {
+                return Err("here-documents cannot be attached to the shell".to_string())
+            }
+            RedirectionTarget::File(word) => {
+                let name = self
+                    .expand_command_args(std::slice::from_ref(word.as_ref()), context)
+                    .map_err(|e| e.to_string())?
+                    .join(" ");
+                let mut options = std::fs::OpenOptions::new();
+                match redirection.operator {
+                    Op::Input => options.read(true),
+                    Op::InputOutput => options.read(true).write(true).create(true),
+                    Op::OutputAppend | Op::OutputBothAppend => options.append(true).create(true),
+                    _ => options.write(true).create(true).truncate(true),
+                };
+                let file = options
+                    .open(context.cwd.join(&name))
+                    .map_err(|e| format!("{name}: {e}"))?;
+                FdTarget::File(file)
             }
-            None
+        };
+        let both = matches!(redirection.operator, Op::OutputBoth | Op::OutputBothAppend);
+        rebind_fd(fd, target).map_err(|e| format!("{fd}: {e}"))?;
+        if both {
+            rebind_fd(2, FdTarget::Duplicate(1)).map_err(|e| format!("2: {e}"))?;
         }
+        Ok(())
+    }
 
-        let mut evaluated_args = Vec::new();
-        for arg in args {
-            match arg {
-                AstNode::Word(s) => {
-                    // First brace expansion
-                    let mut expanded = expand_braces(s);
-                    // Then glob (including extglob) expansion per element
-                    let mut final_args = Vec::new();
-                    for e in expanded.drain(..) {
-                        let globbed = Executor::expand_glob_if_needed(&e, context);
-                        if globbed.is_empty() {
-                            final_args.push(e);
-                        } else {
-                            final_args.extend(globbed);
+    /// `profile run [-o file] script` runs a script on the MIR engine while
+    /// recording a profile, also written to `file` as JSON;
+    /// `profile report [--top n] [--json]` prints the last one
+    fn execute_profile(
+        &mut self,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        match args.split_first() {
+            Some((command, rest)) if command == "run" => self.profile_run(rest, context),
+            Some((command, rest)) if command == "report" => Ok(self.profile_report(rest)),
+            Some((command, _)) => Ok(Self::profile_usage(&format!(
+                "{command}: invalid subcommand\n"
+            ))),
+            None => Ok(Self::profile_usage("")),
+        }
+    }
+
+    fn profile_usage(message: &str) -> ExecutionResult {
+        ExecutionResult::failure(2).with_error(
+            format!(
+                "nxsh: profile: {message}\
+                 profile: usage: profile run [-o file] script or profile report [--top n] [--json]\n"
+            )
+            .into_bytes(),
+        )
+    }
+
+    fn profile_run(
+        &mut self,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let (json_file, script) = match args {
+            [flag, file, script] if flag == "-o" => (Some(file), script),
+            [script] if script != "-o" => (None, script),
+            _ => return Ok(Self::profile_usage("run: one script expected\n")),
+        };
+        let source = match std::fs::read_to_string(context.cwd.join(script)) {
+            Ok(source) => source,
+            Err(e) => {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(format!("nxsh: {script}: {e}\n").into_bytes()))
+            }
+        };
+        let ast = match parse_program(&source) {
+            Ok(ast) => ast,
+            Err(e) => {
+                return Ok(ExecutionResult::failure(2)
+                    .with_error(format!("nxsh: {script}: {e}\n").into_bytes()))
+            }
+        };
+        let start_time = Instant::now();
+        let (program, _) = self.optimize_mir_program(self.compile_ast_to_mir(&ast)?)?;
+
+        self.mir_executor.start_profiling();
+        let result =
+            self.run_mir_program(&program, context, start_time, ExecutionMetrics::default());
+        let profile = self.mir_executor.take_profile().unwrap_or_default();
+        let json_error = json_file.and_then(|file| {
+            std::fs::write(context.cwd.join(file), profile.to_json() + "\n")
+                .err()
+                .map(|e| format!("nxsh: profile: {file}: {e}\n"))
+        });
+        self.last_profile = Some(profile);
+        let mut result = result?;
+        if let Some(error) = json_error {
+            result.stderr.push_str(&error);
+            result.exit_code = 1;
+        }
+        Ok(result)
+    }
+
+    fn profile_report(&self, args: &[String]) -> ExecutionResult {
+        let mut top = 10;
+        let mut json = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => json = true,
+                "--top" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => top = n,
+                    None => return Self::profile_usage("--top: count expected\n"),
+                },
+                other => return Self::profile_usage(&format!("{other}: invalid option\n")),
+            }
+        }
+        let Some(profile) = &self.last_profile else {
+            return ExecutionResult::failure(1)
+                .with_error(b"nxsh: profile: no profile recorded yet\n".to_vec());
+        };
+        let report = if json {
+            profile.to_json() + "\n"
+        } else {
+            profile.report(top)
+        };
+        ExecutionResult::success(0).with_output(report.into_bytes())
+    }
+
+    /// `fc [-e editor] [-lnr] [first [last]]` / `fc -s [old=new] [first]`:
+    /// list a range of history, edit it and run the result, or re-run one
+    /// command with `old` replaced by `new`
+    fn execute_fc(
+        &mut self,
+        args: &[String],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        const USAGE: &str =
+            "fc: usage: fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]\n";
+        let mut list = false;
+        let mut numbered = true;
+        let mut reverse = false;
+        let mut rerun = false;
+        let mut editor = None;
+        let mut rest = args;
+        // `-1` is an operand, not an option
+        while let Some(flag) = rest
+            .first()
+            .filter(|a| a.starts_with('-') && a[1..].starts_with(|c: char| !c.is_ascii_digit()))
+        {
+            rest = &rest[1..];
+            if flag == "--" {
+                break;
+            }
+            for c in flag[1..].chars() {
+                match c {
+                    'l' => list = true,
+                    'n' => numbered = false,
+                    'r' => reverse = true,
+                    's' => rerun = true,
+                    'e' => match rest.split_first() {
+                        Some((name, tail)) => {
+                            editor = Some(name.clone());
+                            rest = tail;
                         }
-                    }
-                    let expanded = final_args;
-                    if expanded.len() == 1 {
-                        evaluated_args.push(expanded.into_iter().next().unwrap());
-                    } else {
-                        for e in expanded {
-                            evaluated_args.push(e);
+                        None => {
+                            return Ok(ExecutionResult::failure(2).with_error(
+                                format!("nxsh: fc: -e: option requires an argument\n{USAGE}")
+                                    .into_bytes(),
+                            ))
                         }
+                    },
+                    _ => {
+                        return Ok(ExecutionResult::failure(2).with_error(
+                            format!("nxsh: fc: -{c}: invalid option\n{USAGE}").into_bytes(),
+                        ))
                     }
                 }
-                AstNode::StringLiteral { value, .. } => evaluated_args.push(value.to_string()),
-                AstNode::NumberLiteral { value, .. } => evaluated_args.push(value.to_string()),
-                AstNode::VariableExpansion { name, .. } => {
-                    evaluated_args.push(context.get_var(name).unwrap_or_else(|| name.to_string()))
+            }
+        }
+
+        let mut history = context.get_history();
+        // The `fc` line itself was recorded before it ran
+        if history.last().is_some_and(|last| Self::is_fc_line(last)) {
+            history.pop();
+        }
+        if history.is_empty() {
+            return Ok(if list {
+                ExecutionResult::success(0)
+            } else {
+                ExecutionResult::failure(1).with_error(b"nxsh: fc: no command found\n".to_vec())
+            });
+        }
+
+        if rerun || editor.as_deref() == Some("-") {
+            let (substitutions, operands): (Vec<&String>, Vec<&String>) =
+                rest.iter().partition(|a| a.contains('='));
+            let index = match operands.first() {
+                Some(spec) => crate::history::find_event(spec, &history),
+                None => Some(history.len() - 1),
+            };
+            let Some(index) = index else {
+                return Ok(ExecutionResult::failure(1)
+                    .with_error(b"nxsh: fc: no command found\n".to_vec()));
+            };
+            let mut command = history[index].clone();
+            for substitution in substitutions {
+                if let Some((old, new)) = substitution.split_once('=') {
+                    if !old.is_empty() {
+                        command = command.replace(old, new);
+                    }
                 }
-                AstNode::CommandSubstitution { command, is_legacy } => {
-                    match self.eval_cmd_substitution(command, context) {
-                        Ok(r) => {
-                            let mut merged = r.stdout;
-                            let stderr_mode = context
-                                .get_var("NXSH_SUBST_STDERR")
-                                .unwrap_or_else(|| "separate".to_string());
-                            if stderr_mode.eq_ignore_ascii_case("merge") && !r.stderr.is_empty() {
-                                if !merged.is_empty() && !merged.ends_with('\n') {
-                                    merged.push('\n');
-                                }
-                                merged.push_str(&r.stderr);
-                            }
-                            let trimmed = merged.trim_end();
-                            let should_split = (*is_legacy)
-                                || context.get_var("NXSH_SUBST_SPLIT").as_deref() == Some("1");
-                            if should_split {
-                                for part in split_fields(trimmed, context) {
-                                    evaluated_args.push(part);
-                                }
-                            } else {
-                                evaluated_args.push(trimmed.to_string());
-                            }
-                        }
-                        Err(_) => evaluated_args.push(String::new()),
+            }
+            return self.run_fc_commands(&command, context);
+        }
+
+        let last_index = history.len() - 1;
+        let (first, last) = match rest {
+            [] if list => (history.len().saturating_sub(16), last_index),
+            [] => (last_index, last_index),
+            [first, tail @ ..] => {
+                let first_index = crate::history::find_event(first, &history);
+                let last_index = match tail.first() {
+                    Some(last) => crate::history::find_event(last, &history),
+                    None if list => Some(last_index),
+                    None => first_index,
+                };
+                match (first_index, last_index) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => {
+                        return Ok(ExecutionResult::failure(1).with_error(
+                            b"nxsh: fc: history specification out of range\n".to_vec(),
+                        ))
                     }
                 }
-                _ => evaluated_args.push(format!("{arg:?}")),
             }
+        };
+        let mut selected: Vec<usize> = (first.min(last)..=first.max(last)).collect();
+        // A range given newest-first runs in that order
+        if reverse != (first > last) {
+            selected.reverse();
         }
-        if let Some(builtin) = self.builtins.get(name) {
-            return builtin.execute(context, &evaluated_args);
+
+        if list {
+            let output: String = selected
+                .iter()
+                .map(|&i| {
+                    if numbered {
+                        format!("{}\t {}\n", i + 1, history[i])
+                    } else {
+                        format!("\t {}\n", history[i])
+                    }
+                })
+                .collect();
+            return Ok(ExecutionResult::success(0).with_output(output.into_bytes()));
         }
-        let start_time = Instant::now();
-        let mut cmd = std::process::Command::new(name);
-        if !evaluated_args.is_empty() {
-            cmd.args(&evaluated_args);
+
+        let editor = editor
+            .or_else(|| context.get_var("FCEDIT"))
+            .or_else(|| context.get_var("EDITOR"))
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        let path = std::env::temp_dir().join(format!("nxsh-fc-{}.sh", std::process::id()));
+        let text: String = selected
+            .iter()
+            .map(|&i| format!("{}\n", history[i]))
+            .collect();
+        if let Err(e) = std::fs::write(&path, text) {
+            return Ok(ExecutionResult::failure(1)
+                .with_error(format!("nxsh: fc: {}: {e}\n", path.display()).into_bytes()));
+        }
+        // The editor may carry its own arguments, like `code --wait`
+        let mut words = editor.split_whitespace().map(str::to_string);
+        let program = words.next().unwrap_or_default();
+        let mut editor_args: Vec<String> = words.collect();
+        editor_args.push(path.to_string_lossy().into_owned());
+        let status = Self::external_command(&program, &editor_args, context).status();
+        let edited = match status {
+            Ok(status) if status.success() => std::fs::read_to_string(&path),
+            Ok(status) => {
+                let _ = std::fs::remove_file(&path);
+                return Ok(ExecutionResult::failure(status.code().unwrap_or(1)));
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Ok(ExecutionResult::failure(127)
+                    .with_error(format!("nxsh: fc: {program}: {e}\n").into_bytes()));
+            }
+        };
+        let _ = std::fs::remove_file(&path);
+        match edited {
+            Ok(commands) => self.run_fc_commands(&commands, context),
+            Err(e) => Ok(ExecutionResult::failure(1)
+                .with_error(format!("nxsh: fc: {}: {e}\n", path.display()).into_bytes())),
+        }
+    }
+
+    /// Whether a history entry is an `fc` command line
+    fn is_fc_line(line: &str) -> bool {
+        line.split_whitespace().next() == Some("fc")
+    }
+
+    /// Echo and run the commands `fc` produced, recording them in the
+    /// history in place of the `fc` line
+    fn run_fc_commands(
+        &mut self,
+        commands: &str,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let commands = commands.trim_end();
+        if commands.trim().is_empty() {
+            return Ok(ExecutionResult::success(0));
+        }
+        if let Ok(mut history) = context.history.lock() {
+            if history.last().is_some_and(|last| Self::is_fc_line(last)) {
+                history.pop();
+            }
+        }
+        for line in commands.lines().filter(|line| !line.trim().is_empty()) {
+            context.add_history(line.to_string());
         }
+        let ast = match parse_program(commands) {
+            Ok(ast) => ast,
+            Err(e) => {
+                return Ok(ExecutionResult::failure(2)
+                    .with_error(format!("{commands}\nnxsh: fc: {e}\n").into_bytes()))
+            }
+        };
+        let mut result = self.execute_ast_direct(&ast, context)?;
+        result.stdout = format!("{commands}\n{}", result.stdout);
+        Ok(result)
+    }
+
+    /// Process command for an external program, run with the shell's
+    /// environment and working directory
+    pub(crate) fn external_command(
+        command: &str,
+        args: &[String],
+        context: &ShellContext,
+    ) -> std::process::Command {
+        let program = context
+            .resolve_command(command)
+            .unwrap_or_else(|| command.into());
+        let mut cmd = std::process::Command::new(program);
+        // The program sees the name it was invoked by, not the hashed path
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::arg0(&mut cmd, command);
+        cmd.args(args);
         if let Ok(env) = context.env.read() {
             for (k, v) in env.iter() {
                 cmd.env(k, v);
             }
         }
         cmd.current_dir(&context.cwd);
-        match cmd.output() {
-            Ok(output) => {
-                let execution_time = start_time.elapsed().as_micros() as u64;
-                Ok(ExecutionResult {
-                    exit_code: output.status.code().unwrap_or(-1),
-                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    execution_time,
-                    strategy: ExecutionStrategy::DirectInterpreter,
-                    metrics: ExecutionMetrics {
-                        compile_time_us: 0,
-                        optimize_time_us: 0,
-                        execute_time_us: execution_time,
-                        instruction_count: 1,
-                        memory_usage: (output.stdout.len() + output.stderr.len()) as u64,
-                    },
-                })
+        cmd
+    }
+
+    /// Hand the terminal to a foreground job, wait until its processes exit
+    /// or it is stopped, then take the terminal back. The first child leads
+    /// the job's process group. Returns each process's status; when the job
+    /// is stopped it moves into the job table and the returned notice
+    /// reports it like `[1]+  Stopped  vim`.
+    #[cfg(unix)]
+    fn wait_foreground_job(
+        description: String,
+        children: Vec<std::process::Child>,
+        terminal: &nxsh_hal::TerminalControl,
+        context: &ShellContext,
+    ) -> ShellResult<(Vec<i32>, String)> {
+        use nxsh_hal::ChildState;
+
+        let Some(pgid) = children.first().map(std::process::Child::id) else {
+            return Ok((Vec::new(), String::new()));
+        };
+        // The leader may not have exec'd yet; it already leads group `pgid`
+        let _ = terminal.give_to(pgid);
+        let mut codes = Vec::with_capacity(children.len());
+        let mut outcome = Ok(None);
+        for child in &children {
+            let state = loop {
+                match nxsh_hal::process::wait_for_state_change(child.id()) {
+                    Ok(ChildState::Continued) => continue,
+                    other => break other,
+                }
+            };
+            match state {
+                Ok(ChildState::Exited(code)) => codes.push(code),
+                Ok(ChildState::Signaled(sig)) => codes.push(128 + sig),
+                Ok(ChildState::Stopped(sig)) => {
+                    outcome = Ok(Some(sig));
+                    break;
+                }
+                Ok(ChildState::Continued) => unreachable!("continues are skipped above"),
+                Err(e) => {
+                    outcome = Err(e);
+                    break;
+                }
+            }
+        }
+        let _ = terminal.reclaim();
+
+        let stopped = outcome.map_err(|e| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Process wait error: {e}"),
+            )
+        })?;
+        let Some(sig) = stopped else {
+            return Ok((codes, String::new()));
+        };
+        // Ctrl-Z stops the whole group; the processes not yet reaped make up
+        // the stopped job
+        let waited = codes.len();
+        codes.resize(children.len(), 128 + sig);
+        let rest = children.into_iter().skip(waited).collect();
+        let job_manager = context.job_manager();
+        let mut manager = crate::builtins::lock_jobs(&job_manager)?;
+        let job_id = manager.adopt_stopped_job(description, rest, pgid)?;
+        let notice = match manager.get_job(job_id)? {
+            Some(job) => format!("\n{}\n", job.status_line(manager.job_marker(job_id))),
+            None => String::new(),
+        };
+        Ok((codes, notice))
+    }
+
+    /// Run a foreground job's processes in one Job Object with the console
+    /// in the mode console programs expect, restoring the shell's mode
+    /// afterwards
+    #[cfg(windows)]
+    fn wait_foreground_job(
+        _description: String,
+        mut children: Vec<std::process::Child>,
+        terminal: &nxsh_hal::TerminalControl,
+        _context: &ShellContext,
+    ) -> ShellResult<(Vec<i32>, String)> {
+        let job = nxsh_hal::process::JobObject::new().ok();
+        if let Some(job) = &job {
+            let _ = job.apply_resource_limits();
+            for child in &children {
+                let _ = job.assign(child);
+            }
+        }
+        let _ = terminal.give_to(children.first().map_or(0, std::process::Child::id));
+        let statuses: std::io::Result<Vec<_>> = children.iter_mut().map(|c| c.wait()).collect();
+        let _ = terminal.reclaim();
+        let statuses = statuses.map_err(|e| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Process wait error: {e}"),
+            )
+        })?;
+        let codes = statuses
+            .iter()
+            .map(|status| status.code().unwrap_or(1))
+            .collect();
+        Ok((codes, String::new()))
+    }
+
+    /// Run a foreground program on a pseudo-terminal of its own, relaying
+    /// the shell's input to it and its output back. The pseudo-terminal
+    /// takes the size of the shell's terminal when it has one, else the size
+    /// `COLUMNS` and `LINES` give.
+    fn execute_on_pty(
+        &self,
+        command: &str,
+        mut cmd: std::process::Command,
+        context: &ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use nxsh_hal::pty::{Pty, DEFAULT_SIZE};
+        use nxsh_hal::terminal::{self, TerminalSize};
+
+        let start_time = Instant::now();
+        let failed = |message: String| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                message,
+            )
+        };
+        let size = terminal::size().unwrap_or_else(|| {
+            let var = |name| context.get_var(name).and_then(|v| v.parse().ok());
+            TerminalSize {
+                columns: var("COLUMNS").unwrap_or(DEFAULT_SIZE.columns),
+                rows: var("LINES").unwrap_or(DEFAULT_SIZE.rows),
+            }
+        });
+        let mut pty = Pty::open(size).map_err(|e| failed(format!("nxsh: {command}: {e}")))?;
+        let mut child = match pty.spawn(&mut cmd) {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(ExecutionResult::failure(127)
+                    .with_error(self.not_found_message(command, context).into_bytes()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Ok(ExecutionResult::failure(126)
+                    .with_error(format!("nxsh: {command}: Permission denied\n").into_bytes()))
             }
             Err(e) => {
-                let execution_time = start_time.elapsed().as_micros() as u64;
-                Ok(ExecutionResult {
-                    exit_code: 127,
-                    stdout: String::new(),
-                    stderr: format!("nxsh: {name}: command not found ({e})"),
-                    execution_time,
-                    strategy: ExecutionStrategy::DirectInterpreter,
-                    metrics: ExecutionMetrics {
-                        compile_time_us: 0,
-                        optimize_time_us: 0,
-                        execute_time_us: execution_time,
-                        instruction_count: 1,
-                        memory_usage: 0,
-                    },
-                })
+                return Err(failed(format!(
+                    "Failed to execute command '{command}': {e}"
+                )))
             }
+        };
+        self.schedule_started(child.id());
+        let status = pty
+            .relay(&mut child)
+            .map_err(|e| failed(format!("nxsh: {command}: {e}")))?;
+
+        let execution_time = start_time.elapsed().as_micros() as u64;
+        let (exit_code, signal_message) = nxsh_hal::command::describe_exit(status);
+        Ok(ExecutionResult {
+            exit_code,
+            stdout: String::new(),
+            stderr: signal_message.map(|m| m + "\n").unwrap_or_default(),
+            execution_time,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics {
+                execute_time_us: execution_time,
+                instruction_count: 1,
+                ..ExecutionMetrics::default()
+            },
+        })
+    }
+
+    /// Execute external process
+    fn execute_external_process(
+        &self,
+        command: &str,
+        args: &[String],
+        context: &ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let direct_cmd = Self::external_command(command, args, context);
+        self.execute_prepared_process(command, args, direct_cmd, context)
+    }
+
+    /// Run `direct_cmd`, the process command for `command` and `args`, on
+    /// the stage's pipes and wait for it
+    fn execute_prepared_process(
+        &self,
+        command: &str,
+        args: &[String],
+        mut direct_cmd: std::process::Command,
+        context: &ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use std::io::ErrorKind as IoErrorKind;
+        #[cfg(windows)]
+        use std::process::Command;
+        use wait_timeout::ChildExt;
+
+        let start_time = Instant::now();
+
+        self.schedule(&mut direct_cmd);
+        let redirect_error = |e: std::io::Error| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Failed to redirect '{command}': {e}"),
+            )
+        };
+        direct_cmd.stdout(self.output.stdio().map_err(redirect_error)?);
+        if let Some(input) = &self.input {
+            direct_cmd.stdin(input.try_clone().map_err(redirect_error)?);
+        }
+
+        let foreground = self.runs_in_foreground(context);
+        // With job control the command runs as its own foreground job;
+        // without, `shopt -s pty` gives it a pseudo-terminal instead
+        let job_terminal = context.terminal.clone().filter(|_| foreground);
+        if foreground && context.terminal.is_none() && context.get_option("pty").unwrap_or(false) {
+            return self.execute_on_pty(command, direct_cmd, context);
+        }
+        // Under `timeout` it gets a process group of its own, so the signal
+        // reaches the programs it starts as well
+        if job_terminal.is_some() || self.deadline.is_some() {
+            nxsh_hal::process::prepare_job_command(&mut direct_cmd, 0);
+        }
+
+        #[cfg(windows)]
+        fn apply_common(cmd: &mut std::process::Command, ctx: &ShellContext) {
+            if let Ok(env) = ctx.env.read() {
+                for (k, v) in env.iter() {
+                    cmd.env(k, v);
+                }
+            }
+            cmd.current_dir(&ctx.cwd);
+        }
+
+        // Try to spawn (not .output()) so we can enforce timeout
+        let mut child = match direct_cmd.spawn() {
+            Ok(c) => c,
+            Err(e) => {
+                #[cfg(windows)]
+                {
+                    if e.kind() == IoErrorKind::NotFound {
+                        let lower = command.to_ascii_lowercase();
+                        let mut fb: Option<std::process::Command> = None;
+                        if lower == "echo" {
+                            // emulate echo via cmd
+                            let mut c = Command::new("cmd.exe");
+                            let mut full = String::from("echo");
+                            for a in args {
+                                full.push(' ');
+                                full.push_str(a);
+                            }
+                            c.args(["/C", &full]);
+                            fb = Some(c);
+                        } else if lower == "sleep" {
+                            // map to Start-Sleep
+                            let seconds = args
+                                .first()
+                                .and_then(|s| s.parse::<u64>().ok())
+                                .unwrap_or(1);
+                            let mut c = Command::new("powershell.exe");
+                            c.args([
+                                "-NoProfile",
+                                "-Command",
+                                &format!("Start-Sleep -Seconds {seconds}"),
+                            ]);
+                            fb = Some(c);
+                        }
+                        if let Some(mut fb_cmd) = fb {
+                            apply_common(&mut fb_cmd, context);
+                            match fb_cmd.spawn() { Ok(c2)=> c2, Err(e2)=> return Err(ShellError::new(
+                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                                format!("Failed to execute command '{command}': {e} (fallback also failed: {e2})")
+                            )) }
+                        } else {
+                            return Err(ShellError::new(
+                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                                format!("Failed to execute command '{command}': {e}"),
+                            ));
+                        }
+                    } else {
+                        return Err(ShellError::new(
+                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                            format!("Failed to execute command '{command}': {e}"),
+                        ));
+                    }
+                }
+                #[cfg(not(windows))]
+                {
+                    let (exit_code, reason) = match e.kind() {
+                        IoErrorKind::NotFound => {
+                            return Ok(ExecutionResult::failure(127)
+                                .with_error(self.not_found_message(command, context).into_bytes()))
+                        }
+                        IoErrorKind::PermissionDenied => (126, "Permission denied".to_string()),
+                        _ => {
+                            return Err(ShellError::new(
+                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                                format!("Failed to execute command '{command}': {e}"),
+                            ))
+                        }
+                    };
+                    return Ok(ExecutionResult::failure(exit_code)
+                        .with_error(format!("nxsh: {command}: {reason}\n").into_bytes()));
+                }
+            }
+        };
+        self.schedule_started(child.id());
+
+        if let Some(terminal) = job_terminal {
+            let description = std::iter::once(command)
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (codes, notice) =
+                Self::wait_foreground_job(description, vec![child], &terminal, context)?;
+            let exit_code = codes.last().copied().unwrap_or(1);
+            let execution_time = start_time.elapsed().as_micros() as u64;
+            return Ok(ExecutionResult {
+                exit_code,
+                stdout: String::new(),
+                stderr: notice,
+                execution_time,
+                strategy: ExecutionStrategy::DirectInterpreter,
+                metrics: ExecutionMetrics {
+                    execute_time_us: execution_time,
+                    instruction_count: 1,
+                    ..ExecutionMetrics::default()
+                },
+            });
+        }
+
+        // Wait with optional per-command timeout
+        let mut timed_out = false;
+        let output = if let Some(deadline) = self.deadline {
+            let (output, signalled) = Self::wait_for_deadline(command, child, deadline, context)?;
+            timed_out = signalled;
+            output
+        } else if let Some(dur) = context.per_command_timeout() {
+            match child.wait_timeout(dur).map_err(|e| {
+                ShellError::new(
+                    ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                    format!("Process wait error: {e}"),
+                )
+            })? {
+                Some(_status) => child.wait_with_output().map_err(|e| {
+                    ShellError::new(
+                        ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                        format!("Process output error: {e}"),
+                    )
+                })?,
+                None => {
+                    // timeout
+                    let _ = child.kill();
+                    return Ok(ExecutionResult {
+                        exit_code: 124,
+                        stdout: String::new(),
+                        stderr: format!("nxsh: command '{command}' timed out"),
+                        execution_time: start_time.elapsed().as_micros() as u64,
+                        strategy: ExecutionStrategy::DirectInterpreter,
+                        metrics: ExecutionMetrics::default(),
+                    });
+                }
+            }
+        } else {
+            child.wait_with_output().map_err(|e| {
+                ShellError::new(
+                    ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                    format!("Process output error: {e}"),
+                )
+            })?
+        };
+
+        let execution_time = start_time.elapsed().as_micros() as u64;
+        let (exit_code, signal_message) = nxsh_hal::command::describe_exit(output.status);
+        let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        // A program `timeout` stopped was expected to die of the signal
+        if let Some(message) = signal_message.filter(|_| !timed_out) {
+            stderr.push_str(&message);
+            stderr.push('\n');
+        }
+        Ok(ExecutionResult {
+            exit_code,
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr,
+            execution_time,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics {
+                compile_time_us: 0,
+                optimize_time_us: 0,
+                execute_time_us: execution_time,
+                instruction_count: 1,
+                memory_usage: (output.stdout.len() + output.stderr.len()) as u64,
+            },
+        })
+    }
+
+    /// Execute a single command
+    #[allow(dead_code)]
+    fn execute_command(
+        &mut self,
+        name: &str,
+        args: &[AstNode],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        // Advanced (yet bounded) brace expansion supporting:
+        //  - comma lists: {a,b,c}
+        //  - nested lists: {a,{b,c}}
+        //  - numeric ranges: {1..3} => 1 2 3 ; with optional step {1..10..2}
+        //  - alpha ranges: {a..c} => a b c
+        // Limit expansions to avoid exponential blowup (MAX_EXPANSIONS)
+        fn expand_braces(input: &str) -> Vec<String> {
+            const MAX_EXPANSIONS: usize = 4096; // safety cap
+                                                // Provide escape handling: backslash preceding { } , prevents structural meaning.
+                                                // Strategy: temporarily replace escaped tokens with sentinel bytes, run normal logic, then restore.
+            const ESC_LBRACE: char = '\u{1F}';
+            const ESC_RBRACE: char = '\u{1E}';
+            const ESC_COMMA: char = '\u{1D}';
+            let mut transformed = String::with_capacity(input.len());
+            let mut chars = input.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    // escape next char if brace related
+                    if let Some(&next) = chars.peek() {
+                        match next {
+                            '{' => {
+                                transformed.push(ESC_LBRACE);
+                                chars.next();
+                                continue;
+                            }
+                            '}' => {
+                                transformed.push(ESC_RBRACE);
+                                chars.next();
+                                continue;
+                            }
+                            ',' => {
+                                transformed.push(ESC_COMMA);
+                                chars.next();
+                                continue;
+                            }
+                            _ => {
+                                transformed.push(next);
+                                chars.next();
+                                continue;
+                            }
+                        }
+                    }
+                    // trailing backslash -> keep
+                    transformed.push('\\');
+                } else {
+                    transformed.push(c);
+                }
+            }
+            // Fast path: if no '{' present return original (after restoration)
+            if !transformed.as_bytes().contains(&b'{') {
+                return vec![input.to_string()];
+            }
+            fn restore(s: String) -> String {
+                let mut out = String::with_capacity(s.len());
+                for c in s.chars() {
+                    match c {
+                        '\u{1F}' => {
+                            out.push('\\');
+                            out.push('{');
+                        }
+                        '\u{1E}' => {
+                            out.push('\\');
+                            out.push('}');
+                        }
+                        '\u{1D}' => {
+                            out.push('\\');
+                            out.push(',');
+                        }
+                        _ => out.push(c),
+                    }
+                }
+                out
+            }
+            // Find first top-level {...}
+            let bytes = transformed.as_bytes();
+            let mut level = 0usize;
+            let mut start_idx = None;
+            for (i, &b) in bytes.iter().enumerate() {
+                match b {
+                    b'{' => {
+                        if level == 0 {
+                            start_idx = Some(i);
+                        }
+                        level += 1;
+                    }
+                    b'}' => {
+                        if level > 0 {
+                            level -= 1;
+                            if level == 0 {
+                                // complete group
+                                let open = start_idx.unwrap();
+                                let inner = &transformed[open + 1..i];
+                                let prefix = &transformed[..open];
+                                let suffix = &transformed[i + 1..];
+                                // Determine if inner should expand: top-level comma or a valid range pattern
+                                let mut has_top_level_comma = false;
+                                {
+                                    let mut lvl = 0usize;
+                                    for ch in inner.chars() {
+                                        match ch {
+                                            '{' => lvl += 1,
+                                            '}' => {
+                                                if lvl > 0 {
+                                                    lvl = lvl.saturating_sub(1);
+                                                }
+                                            }
+                                            ',' if lvl == 0 => {
+                                                has_top_level_comma = true;
+                                                break;
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                }
+                                let variants = if has_top_level_comma || try_range(inner).is_some()
+                                {
+                                    parse_brace_inner(inner)
+                                } else {
+                                    // Not expandable: keep literal braces
+                                    vec![format!("{{{}}}", inner)]
+                                };
+                                let suffix_expanded = expand_braces(suffix);
+                                let mut out = Vec::new();
+                                for v in variants {
+                                    let v_expanded = expand_braces(&v);
+                                    for ve in v_expanded {
+                                        for tail in &suffix_expanded {
+                                            out.push(restore(format!("{prefix}{ve}{tail}")));
+                                            if out.len() >= MAX_EXPANSIONS {
+                                                // mark truncation via env var for diagnostics
+                                                std::env::set_var(
+                                                    "NXSH_BRACE_EXPANSION_TRUNCATED",
+                                                    "1",
+                                                );
+                                                return out;
+                                            }
+                                        }
+                                    }
+                                }
+                                return out;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            vec![input.to_string()] // no complete group
+        }
+
+        fn parse_brace_inner(inner: &str) -> Vec<String> {
+            // Detect range patterns first: {start..end[..step]}
+            if let Some(range_variants) = try_range(inner) {
+                return range_variants;
+            }
+            let mut parts = Vec::new();
+            let mut level = 0usize;
+            let mut escape = false;
+            let mut current = String::new();
+            for c in inner.chars() {
+                if escape {
+                    current.push(c);
+                    escape = false;
+                    continue;
+                }
+                match c {
+                    '\\' => {
+                        escape = true;
+                    }
+                    '{' => {
+                        level += 1;
+                        current.push(c);
+                    }
+                    '}' => {
+                        if level > 0 {
+                            level = level.saturating_sub(1);
+                        }
+                        current.push(c);
+                    }
+                    ',' if level == 0 => {
+                        parts.push(current.to_string());
+                        current.clear();
+                    }
+                    _ => current.push(c),
+                }
+            }
+            // Allow trailing empty element {a,b,}
+            if escape {
+                current.push('\\');
+            }
+            parts.push(current.to_string());
+            parts
+        }
+
+        fn try_range(inner: &str) -> Option<Vec<String>> {
+            // Numeric or alpha range like 1..5 or a..f or 1..10..2
+            let mut segs = inner.split("..").collect::<Vec<_>>();
+            if segs.len() < 2 {
+                return None;
+            }
+            if segs.len() > 3 {
+                return None;
+            }
+            let mut step_abs = if segs.len() == 3 {
+                segs.pop()?.parse::<i64>().ok()?
+            } else {
+                1
+            };
+            if step_abs == 0 {
+                return None;
+            }
+            step_abs = step_abs.abs();
+            let end_str = segs.pop()?;
+            let start_str = segs.pop()?;
+            // numeric
+            if let (Ok(start), Ok(end)) = (start_str.parse::<i64>(), end_str.parse::<i64>()) {
+                let dir = if end >= start { 1 } else { -1 };
+                let step = step_abs * dir;
+                let mut out = Vec::new();
+                let mut v = start;
+                while (step > 0 && v <= end) || (step < 0 && v >= end) {
+                    out.push(v.to_string());
+                    v += step;
+                    if out.len() >= 2048 {
+                        break;
+                    }
+                }
+                return Some(out);
+            }
+            // alpha single char
+            if start_str.len() == 1 && end_str.len() == 1 {
+                let (a, b) = (
+                    start_str.chars().next().unwrap(),
+                    end_str.chars().next().unwrap(),
+                );
+                if !a.is_ascii_alphabetic() || !b.is_ascii_alphabetic() {
+                    return None;
+                }
+                let (ai, bi) = (a as i16, b as i16);
+                let dir: i16 = if bi >= ai { 1 } else { -1 };
+                let step: i16 = (step_abs as i16) * dir;
+                let mut out = Vec::new();
+                let mut cur = ai;
+                while (step > 0 && cur <= bi) || (step < 0 && cur >= bi) {
+                    out.push(char::from_u32(cur as u32).unwrap().to_string());
+                    cur += step;
+                    if out.len() >= 2048 {
+                        break;
+                    }
+                }
+                return Some(out);
+            }
+            None
+        }
+
+        let mut evaluated_args = Vec::new();
+        for arg in args {
+            match arg {
+                AstNode::Word(s) if s.contains("$(") || s.contains('`') => {
+                    evaluated_args.push(self.substituted_word(s, context)?);
+                }
+                AstNode::Word(s) => {
+                    // First brace expansion
+                    let mut expanded = expand_braces(s);
+                    // Then glob (including extglob) expansion per element
+                    let mut final_args = Vec::new();
+                    for e in expanded.drain(..) {
+                        let globbed = Executor::expand_glob_if_needed(&e, context);
+                        if globbed.is_empty() {
+                            final_args.push(e);
+                        } else {
+                            final_args.extend(globbed);
+                        }
+                    }
+                    let expanded = final_args;
+                    if expanded.len() == 1 {
+                        evaluated_args.push(expanded.into_iter().next().unwrap());
+                    } else {
+                        for e in expanded {
+                            evaluated_args.push(e);
+                        }
+                    }
+                }
+                AstNode::StringLiteral { value, .. } => evaluated_args.push(value.to_string()),
+                AstNode::NumberLiteral { value, .. } => evaluated_args.push(value.to_string()),
+                AstNode::VariableExpansion { name, modifier } => {
+                    let expr = Self::parameter_expr(name, modifier);
+                    evaluated_args.extend(Self::expand_variable_fields(&expr, context)?);
+                }
+                AstNode::VariableAssignment { .. } | AstNode::ArrayAssignment { .. } => {
+                    evaluated_args.push(self.assignment_word(arg, context)?);
+                }
+                AstNode::CommandSubstitution { command, is_legacy } => {
+                    evaluated_args.extend(self.substitution_fields(command, *is_legacy, context));
+                }
+                _ => evaluated_args.push(format!("{arg:?}")),
+            }
+        }
+        if let Some(builtin) = self.builtins.get(name) {
+            return builtin.execute(context, &evaluated_args);
+        }
+        let start_time = Instant::now();
+        let mut cmd = std::process::Command::new(name);
+        if !evaluated_args.is_empty() {
+            cmd.args(&evaluated_args);
+        }
+        if let Ok(env) = context.env.read() {
+            for (k, v) in env.iter() {
+                cmd.env(k, v);
+            }
+        }
+        cmd.current_dir(&context.cwd);
+        match cmd.output() {
+            Ok(output) => {
+                let execution_time = start_time.elapsed().as_micros() as u64;
+                Ok(ExecutionResult {
+                    exit_code: output.status.code().unwrap_or(-1),
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    execution_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics {
+                        compile_time_us: 0,
+                        optimize_time_us: 0,
+                        execute_time_us: execution_time,
+                        instruction_count: 1,
+                        memory_usage: (output.stdout.len() + output.stderr.len()) as u64,
+                    },
+                })
+            }
+            Err(e) => {
+                let execution_time = start_time.elapsed().as_micros() as u64;
+                Ok(ExecutionResult {
+                    exit_code: 127,
+                    stdout: String::new(),
+                    stderr: match e.kind() {
+                        std::io::ErrorKind::NotFound => self.not_found_message(name, context),
+                        _ => format!("nxsh: {name}: {e}\n"),
+                    },
+                    execution_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics {
+                        compile_time_us: 0,
+                        optimize_time_us: 0,
+                        execute_time_us: execution_time,
+                        instruction_count: 1,
+                        memory_usage: 0,
+                    },
+                })
+            }
+        }
+    }
+
+    /// Evaluate args expansion & splitting like execute_command would
+    pub(crate) fn evaluate_args(
+        &mut self,
+        args: &[AstNode],
+        context: &mut ShellContext,
+    ) -> Vec<String> {
+        let mut evaluated = Vec::new();
+        for arg in args {
+            match arg {
+                AstNode::Word(s) => evaluated.push(s.to_string()),
+                AstNode::StringLiteral { value, .. } => evaluated.push(value.to_string()),
+                AstNode::NumberLiteral { value, .. } => evaluated.push(value.to_string()),
+                AstNode::VariableExpansion { name, .. } => {
+                    evaluated.push(context.get_var(name).unwrap_or_else(|| name.to_string()))
+                }
+                AstNode::CommandSubstitution { command, is_legacy } => {
+                    evaluated.extend(self.substitution_fields(command, *is_legacy, context));
+                }
+                _ => evaluated.push(format!("{arg:?}")),
+            }
+        }
+        evaluated
+    }
+
+    /// Execute a pipeline of commands
+    fn execute_pipeline(
+        &mut self,
+        commands: &[AstNode],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let _start_time = Instant::now();
+        if let Some(stages) = self.structured_stages(commands, context) {
+            return self.execute_structured_pipeline(stages, context);
+        }
+        // Fast path on Windows: delegate to cmd.exe to get real pipe semantics across externals/builtins
+        #[cfg(windows)]
+        {
+            use std::process::Command;
+            // Helper to serialize a simple Command node into a shell-friendly string
+            fn cmd_to_string(cmd: &AstNode) -> Option<String> {
+                if let AstNode::Command { name, args, .. } = cmd {
+                    let name_str = match name.as_ref() {
+                        AstNode::Word(w) => w.to_string(),
+                        AstNode::StringLiteral { value, .. } => value.to_string(),
+                        _ => return None,
+                    };
+                    let mut parts = vec![name_str];
+                    for a in args {
+                        match a {
+                            AstNode::Word(w) => parts.push(w.to_string()),
+                            AstNode::StringLiteral { value, .. } => {
+                                // Quote with simple CMD-safe quoting: double inner quotes, then wrap
+                                let s = value.replace('"', "\"\"");
+                                parts.push(format!("\"{s}\""));
+                            }
+                            AstNode::NumberLiteral { value, .. } => parts.push(value.to_string()),
+                            AstNode::VariableExpansion { name, .. } => {
+                                parts.push(format!("%{name}%"))
+                            }
+                            _ => parts.push(format!("{a:?}")),
+                        }
+                    }
+                    Some(parts.join(" "))
+                } else {
+                    None
+                }
+            }
+            let mut serialized: Vec<String> = Vec::new();
+            for c in commands {
+                if let Some(s) = cmd_to_string(c) {
+                    serialized.push(s);
+                } else {
+                    serialized.clear();
+                    break;
+                }
+            }
+            if !serialized.is_empty() && serialized.len() >= 2 {
+                let pipeline_str = serialized.join(" | ");
+                let start_time = Instant::now();
+                let mut cmd = Command::new("cmd.exe");
+                cmd.args(["/C", &pipeline_str]);
+                // Propagate env and cwd
+                if let Ok(env) = context.env.read() {
+                    for (k, v) in env.iter() {
+                        cmd.env(k, v);
+                    }
+                }
+                cmd.current_dir(&context.cwd);
+                // Execute with optional timeout using wait_timeout like external process path
+                use wait_timeout::ChildExt;
+                let mut child = cmd.spawn().map_err(|e| {
+                    ShellError::new(
+                        ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                        format!("Failed to execute pipeline: {e}"),
+                    )
+                })?;
+                let output = if let Some(dur) = context.per_command_timeout() {
+                    match child.wait_timeout(dur).map_err(|e| {
+                        ShellError::new(
+                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                            format!("Process wait error: {e}"),
+                        )
+                    })? {
+                        Some(_status) => child.wait_with_output().map_err(|e| {
+                            ShellError::new(
+                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                                format!("Process output error: {e}"),
+                            )
+                        })?,
+                        None => {
+                            let _ = child.kill();
+                            return Ok(ExecutionResult {
+                                exit_code: 124,
+                                stdout: String::new(),
+                                stderr: "nxsh: pipeline timed out".to_string(),
+                                execution_time: start_time.elapsed().as_micros() as u64,
+                                strategy: ExecutionStrategy::DirectInterpreter,
+                                metrics: ExecutionMetrics::default(),
+                            });
+                        }
+                    }
+                } else {
+                    child.wait_with_output().map_err(|e| {
+                        ShellError::new(
+                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                            format!("Process output error: {e}"),
+                        )
+                    })?
+                };
+                let execution_time = start_time.elapsed().as_micros() as u64;
+                return Ok(ExecutionResult {
+                    exit_code: output.status.code().unwrap_or(-1),
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    execution_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics {
+                        execute_time_us: execution_time,
+                        ..Default::default()
+                    },
+                });
+            }
+        }
+        // Experimental: if PowerShell compatibility requested, attempt object pipeline using simplified textual reconstruction
+        if std::env::var("NXSH_PWSH_MODE").ok().as_deref() == Some("1") {
+            #[cfg(feature = "powershell_compat")]
+            {
+                let mut parts = Vec::new();
+                for c in commands.iter() {
+                    parts.push(format!("{c:?}"));
+                }
+                let pipeline_str = parts.join(" | ");
+                let mut compat = crate::powershell_compat::PowerShellCompat::new();
+                if let Ok(objs) = compat.execute_pipeline(&pipeline_str) {
+                    let out = objs
+                        .iter()
+                        .map(|o| o.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let execution_time = _start_time.elapsed().as_micros() as u64;
+                    return Ok(ExecutionResult {
+                        exit_code: 0,
+                        stdout: out,
+                        stderr: String::new(),
+                        execution_time,
+                        strategy: ExecutionStrategy::DirectInterpreter,
+                        metrics: ExecutionMetrics {
+                            execute_time_us: execution_time,
+                            ..Default::default()
+                        },
+                    });
+                }
+            }
+        }
+        let mut final_result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            execution_time: 0,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics::default(),
+        };
+
+        // Every element runs; the pipeline's status is the last element's, or
+        // with `set -o pipefail` the rightmost non-zero status
+        let pipefail = context.get_option("pipefail").unwrap_or(false);
+        let pipes = (1..commands.len())
+            .map(|_| nxsh_hal::pipe::stream_pipe())
+            .collect::<std::io::Result<Vec<_>>>();
+        let results = match pipes {
+            Ok(pipes) => self.execute_connected_stages(commands, pipes, context)?,
+            // Without OS pipes the elements run one after another
+            Err(_) => {
+                let mut results = Vec::new();
+                for command in commands {
+                    if context.is_timed_out() {
+                        final_result.exit_code = 124;
+                        final_result.stderr.push_str("nxsh: pipeline timed out");
+                        return Ok(final_result);
+                    }
+                    results.push(self.execute_tested(command, context)?);
+                }
+                results
+            }
+        };
+        for result in results {
+            final_result.execution_time += result.execution_time;
+            final_result.stdout = result.stdout;
+            final_result.stderr.push_str(&result.stderr);
+            if !pipefail || result.exit_code != 0 {
+                final_result.exit_code = result.exit_code;
+            }
+        }
+
+        Ok(final_result)
+    }
+
+    /// Run the elements of a pipeline at the same time, each reading the
+    /// previous one's output through a pipe while it is written. External
+    /// commands are spawned as processes. Builtins, functions and compound
+    /// commands run in the shell with the pipes as stdin and stdout: the last
+    /// on this thread, the others each on a thread of their own with a
+    /// subshell's copy of the context. A pipe's bounded buffer holds a
+    /// writer back until its reader catches up.
+    fn execute_connected_stages(
+        &mut self,
+        commands: &[AstNode],
+        pipes: Vec<(File, File)>,
+        context: &mut ShellContext,
+    ) -> ShellResult<Vec<ExecutionResult>> {
+        let Some(last) = commands.len().checked_sub(1) else {
+            return Ok(Vec::new());
+        };
+        let mut externals = Vec::with_capacity(commands.len());
+        for command in commands {
+            externals.push(self.external_stage(command, context)?);
+        }
+        // Element `i` reads `inputs[i]` and writes `outputs[i]`; `None` is
+        // the pipeline's own input or output
+        let mut inputs = vec![None];
+        let mut outputs = Vec::new();
+        for (read, write) in pipes {
+            outputs.push(Some(write));
+            inputs.push(Some(read));
+        }
+        outputs.push(None);
+
+        // A pipeline of programs alone is one foreground job: its processes
+        // share a process group that gets the terminal, so Ctrl-C and Ctrl-Z
+        // reach every stage
+        let job_terminal = context
+            .terminal
+            .clone()
+            .filter(|_| externals.iter().all(Option::is_some) && self.runs_in_foreground(context));
+
+        let mut results: Vec<Option<ExecutionResult>> = vec![None; commands.len()];
+        let mut children: Vec<(usize, &str, std::process::Child)> = Vec::new();
+        for (i, stage) in externals.iter().enumerate() {
+            let Some((name, args)) = stage else {
+                continue;
+            };
+            let (stdin, stdout) = (inputs[i].take(), outputs[i].take());
+            // The first process started leads the group the others join
+            let pgid = job_terminal
+                .as_ref()
+                .map(|_| children.first().map_or(0, |(_, _, child)| child.id()));
+            match self.spawn_stage(name, args, stdin, stdout, pgid, context) {
+                Ok(child) => children.push((i, name.as_str(), child)),
+                Err(e) => results[i] = Some(self.spawn_failure(name, &e, context)),
+            }
+        }
+        let mut error = None;
+        std::thread::scope(|scope| -> ShellResult<()> {
+            let mut threads = Vec::new();
+            for (i, command) in commands.iter().enumerate().take(last) {
+                if externals[i].is_some() {
+                    continue;
+                }
+                let mut stage_context = match context.create_subcontext() {
+                    Ok(stage_context) => stage_context,
+                    Err(e) => {
+                        error = Some(ShellError::new(
+                            ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
+                            format!("pipeline: {e}"),
+                        ));
+                        break;
+                    }
+                };
+                if let Ok(mut options) = stage_context.options.write() {
+                    options.subshell_level += 1;
+                }
+                let mut executor = self.for_pipeline_stage();
+                // The first stage reads whatever the pipeline does
+                let input = inputs[i]
+                    .take()
+                    .or_else(|| self.input.as_ref().and_then(|input| input.try_clone().ok()));
+                let output = outputs[i].take();
+                let thread = scope.spawn(move || {
+                    executor.execute_piped_stage(command, input, output, &mut stage_context)
+                });
+                threads.push((i, thread));
+            }
+            // Close the ends no stage took, so their readers see end of file
+            for end in inputs[..last].iter_mut().chain(&mut outputs) {
+                end.take();
+            }
+
+            if error.is_none() && externals[last].is_none() {
+                let input = inputs[last].take();
+                match self.execute_piped_stage(&commands[last], input, None, context) {
+                    Ok(result) => results[last] = Some(result),
+                    Err(e) => error = Some(e),
+                }
+            }
+            let notice = self.wait_stages(
+                children,
+                &externals,
+                " | ",
+                job_terminal.as_deref(),
+                &mut results,
+                context,
+            )?;
+            if let Some(result) = results[last].as_mut() {
+                result.stderr.push_str(&notice);
+            }
+            for (i, thread) in threads {
+                match thread.join() {
+                    Ok(Ok(result)) => results[i] = Some(result),
+                    Ok(Err(e)) => {
+                        error.get_or_insert(e);
+                    }
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            Ok(())
+        })?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(results.into_iter().flatten().collect()),
+        }
+    }
+
+    /// Run the members of an `a &&& b` job group at the same time and wait
+    /// for all of them. Programs are spawned as processes, which form one
+    /// foreground job when every member is one. Anything else runs on a
+    /// thread of its own with a subshell's copy of the context, so its
+    /// assignments stay there. Output comes back member by member, and the
+    /// group's status is the rightmost non-zero one.
+    fn execute_job_group(
+        &mut self,
+        members: &[AstNode],
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let mut externals = Vec::with_capacity(members.len());
+        for member in members {
+            externals.push(self.external_stage(member, context)?);
+        }
+        let job_terminal = context
+            .terminal
+            .clone()
+            .filter(|_| externals.iter().all(Option::is_some) && self.runs_in_foreground(context));
+
+        let mut results: Vec<Option<ExecutionResult>> = vec![None; members.len()];
+        let mut children: Vec<(usize, &str, std::process::Child)> = Vec::new();
+        for (i, member) in externals.iter().enumerate() {
+            let Some((name, args)) = member else {
+                continue;
+            };
+            let pgid = job_terminal
+                .as_ref()
+                .map(|_| children.first().map_or(0, |(_, _, child)| child.id()));
+            match self.spawn_stage(name, args, None, None, pgid, context) {
+                Ok(child) => children.push((i, name.as_str(), child)),
+                Err(e) => results[i] = Some(self.spawn_failure(name, &e, context)),
+            }
+        }
+        let mut error = None;
+        let mut notice = String::new();
+        std::thread::scope(|scope| -> ShellResult<()> {
+            let mut threads = Vec::new();
+            for (i, member) in members.iter().enumerate() {
+                if externals[i].is_some() {
+                    continue;
+                }
+                let mut member_context = match context.create_subcontext() {
+                    Ok(member_context) => member_context,
+                    Err(e) => {
+                        error = Some(ShellError::new(
+                            ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
+                            format!("job group: {e}"),
+                        ));
+                        break;
+                    }
+                };
+                if let Ok(mut options) = member_context.options.write() {
+                    options.subshell_level += 1;
+                }
+                let mut executor = self.for_pipeline_stage();
+                let input = self.input.as_ref().and_then(|input| input.try_clone().ok());
+                // Every member writes where the group does
+                let (output, captured) = match &self.output {
+                    OutputTarget::Inherit => (None, None),
+                    OutputTarget::Pipe(pipe) => (pipe.try_clone().ok(), None),
+                    OutputTarget::Capture => {
+                        let captured = Stream::new(StreamType::Byte);
+                        member_context.stdout = Box::new(captured.clone());
+                        executor.output = OutputTarget::Capture;
+                        (None, Some(captured))
+                    }
+                };
+                let thread = scope.spawn(move || {
+                    let mut result =
+                        executor.execute_piped_stage(member, input, output, &mut member_context)?;
+                    if let Some(captured) = captured {
+                        let written = String::from_utf8_lossy(&captured.to_bytes()?).into_owned();
+                        result.stdout.insert_str(0, &written);
+                    }
+                    Ok(result)
+                });
+                threads.push((i, thread));
+            }
+            notice = self.wait_stages(
+                children,
+                &externals,
+                " &&& ",
+                job_terminal.as_deref(),
+                &mut results,
+                context,
+            )?;
+            for (i, thread) in threads {
+                match thread.join() {
+                    Ok(Ok(result)) => results[i] = Some(result),
+                    Ok(Err(e)) => {
+                        error.get_or_insert(e);
+                    }
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            Ok(())
+        })?;
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let mut group_result = ExecutionResult::success(0);
+        for result in results.into_iter().flatten() {
+            group_result.execution_time += result.execution_time;
+            group_result.stdout.push_str(&result.stdout);
+            group_result.stderr.push_str(&result.stderr);
+            if result.exit_code != 0 {
+                group_result.exit_code = result.exit_code;
+            }
+        }
+        group_result.stderr.push_str(&notice);
+        Ok(group_result)
+    }
+
+    /// Wait for the processes started for a pipeline or job group, putting
+    /// each one's result at its element's index. With `job_terminal` they are
+    /// one foreground job, described by their command lines joined with
+    /// `separator`, and the notice of a stop is returned. Otherwise each is
+    /// killed once the command's time limit passes, waiting from the last
+    /// element back, which drains captured output while the elements
+    /// feeding it still run.
+    fn wait_stages(
+        &self,
+        children: Vec<(usize, &str, std::process::Child)>,
+        externals: &[Option<(String, Vec<String>)>],
+        separator: &str,
+        job_terminal: Option<&nxsh_hal::TerminalControl>,
+        results: &mut [Option<ExecutionResult>],
+        context: &ShellContext,
+    ) -> ShellResult<String> {
+        let Some(terminal) = job_terminal else {
+            let deadline = context
+                .per_command_timeout()
+                .map(|timeout| Instant::now() + timeout)
+                .into_iter()
+                .chain(self.deadline.map(|deadline| deadline.at))
+                .min();
+            for (i, name, child) in children.into_iter().rev() {
+                results[i] = Some(Self::wait_stage(name, child, deadline)?);
+            }
+            return Ok(String::new());
+        };
+        let description = externals
+            .iter()
+            .flatten()
+            .map(|(name, args)| {
+                std::iter::once(name)
+                    .chain(args)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(separator);
+        let (stages, processes): (Vec<_>, Vec<_>) =
+            children.into_iter().map(|(i, _, child)| (i, child)).unzip();
+        let (codes, notice) = Self::wait_foreground_job(description, processes, terminal, context)?;
+        for (i, exit_code) in stages.into_iter().zip(codes) {
+            results[i] = Some(ExecutionResult::failure(exit_code));
+        }
+        Ok(notice)
+    }
+
+    /// Name and expanded arguments of a pipeline element that runs an
+    /// external program, which can be spawned alongside the other stages
+    fn external_stage(
+        &mut self,
+        command: &AstNode,
+        context: &mut ShellContext,
+    ) -> ShellResult<Option<(String, Vec<String>)>> {
+        let AstNode::Command {
+            name,
+            args,
+            redirections,
+            background: false,
+        } = command
+        else {
+            return Ok(None);
+        };
+        let name = match name.as_ref() {
+            AstNode::Word(word) => *word,
+            AstNode::StringLiteral { value, .. } => *value,
+            _ => return Ok(None),
+        };
+        if !redirections.is_empty()
+            || Self::SHELL_COMMANDS.contains(&name)
+            || context.has_function(name)
+            || self.builtins.contains_key(name)
+            || context.plugin_commands.contains(name)
+        {
+            return Ok(None);
+        }
+        let args = self.expand_command_args(args, context)?;
+        if context.get_option("xtrace").unwrap_or(false) {
+            let trace = Self::format_trace(name, &args, context);
+            let _ = context.stderr.write_all(trace.as_bytes());
+            let _ = context.stderr.flush();
+        }
+        Ok(Some((name.to_string(), args)))
+    }
+
+    /// Start an external pipeline stage without waiting for it. Without a
+    /// pipe it reads and writes what the pipeline as a whole does. With
+    /// `pgid` it joins that process group as part of a job, or leads a new
+    /// one when `pgid` is 0.
+    fn spawn_stage(
+        &self,
+        name: &str,
+        args: &[String],
+        stdin: Option<File>,
+        stdout: Option<File>,
+        pgid: Option<nxsh_hal::process::ProcessGroupId>,
+        context: &ShellContext,
+    ) -> std::io::Result<std::process::Child> {
+        let mut cmd = Self::external_command(name, args, context);
+        self.schedule(&mut cmd);
+        if let Some(pgid) = pgid {
+            nxsh_hal::process::prepare_job_command(&mut cmd, pgid);
+        }
+        let stdin = match stdin {
+            Some(pipe) => Some(pipe),
+            None => self.input.as_ref().map(File::try_clone).transpose()?,
+        };
+        if let Some(stdin) = stdin {
+            cmd.stdin(stdin);
+        }
+        cmd.stdout(match stdout {
+            Some(pipe) => std::process::Stdio::from(pipe),
+            None => self.output.stdio()?,
+        });
+        let child = cmd.spawn()?;
+        self.schedule_started(child.id());
+        Ok(child)
+    }
+
+    /// Whether a command runs in the foreground with the terminal as its
+    /// input and output, not feeding a pipe or substitution or held to a
+    /// time limit
+    fn runs_in_foreground(&self, context: &ShellContext) -> bool {
+        self.output.is_inherit()
+            && self.input.is_none()
+            && context.per_command_timeout().is_none()
+            && self.deadline.is_none()
+    }
+
+    /// Result of an external command that could not be started
+    fn spawn_failure(
+        &self,
+        name: &str,
+        error: &std::io::Error,
+        context: &ShellContext,
+    ) -> ExecutionResult {
+        let (exit_code, message) = match error.kind() {
+            std::io::ErrorKind::NotFound => (127, self.not_found_message(name, context)),
+            std::io::ErrorKind::PermissionDenied => {
+                (126, format!("nxsh: {name}: Permission denied\n"))
+            }
+            _ => (126, format!("nxsh: {name}: {error}\n")),
+        };
+        ExecutionResult::failure(exit_code).with_error(message.into_bytes())
+    }
+
+    /// Wait for a spawned pipeline stage, killing it once `deadline` passes
+    fn wait_stage(
+        name: &str,
+        mut child: std::process::Child,
+        deadline: Option<Instant>,
+    ) -> ShellResult<ExecutionResult> {
+        use wait_timeout::ChildExt;
+
+        let wait_error = |e: std::io::Error| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Process wait error: {e}"),
+            )
+        };
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if child.wait_timeout(remaining).map_err(wait_error)?.is_none() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(ExecutionResult::failure(124)
+                    .with_error(format!("nxsh: command '{name}' timed out").into_bytes()));
+            }
+        }
+        let output = child.wait_with_output().map_err(wait_error)?;
+        let (exit_code, signal_message) = nxsh_hal::command::describe_exit(output.status);
+        let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if let Some(message) = signal_message {
+            stderr.push_str(&message);
+            stderr.push('\n');
+        }
+        Ok(ExecutionResult {
+            exit_code,
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr,
+            ..ExecutionResult::failure(exit_code)
+        })
+    }
+
+    /// Run an element of a pipeline in the shell, reading `input` and
+    /// writing `output` in place of the shell's own stdin and stdout. A stage
+    /// whose reader has gone ends with no error and status 141, as a process
+    /// killed by SIGPIPE does.
+    fn execute_piped_stage(
+        &mut self,
+        command: &AstNode,
+        input: Option<File>,
+        output: Option<File>,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use std::io::Write;
+
+        let pipe_error = |e: std::io::Error| {
+            ShellError::new(
+                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
+                format!("Pipe error: {e}"),
+            )
+        };
+        let stdin: Option<Box<dyn std::io::Read + Send>> = match &input {
+            Some(pipe) => Some(Box::new(pipe.try_clone().map_err(pipe_error)?)),
+            None => None,
+        };
+        let writer = match &output {
+            Some(pipe) => Some(PipeWriter::new(pipe.try_clone().map_err(pipe_error)?)),
+            None => None,
+        };
+        let stdout: Option<Box<dyn Write + Send>> = match &writer {
+            Some(writer) => Some(Box::new(writer.try_clone().map_err(pipe_error)?)),
+            None => None,
+        };
+
+        let saved_stdin = stdin.map(|stdin| std::mem::replace(&mut context.stdin, stdin));
+        let saved_input = input.map(|input| self.input.replace(input));
+        let saved_stdout = stdout.map(|stdout| std::mem::replace(&mut context.stdout, stdout));
+        let saved_output =
+            output.map(|pipe| std::mem::replace(&mut self.output, OutputTarget::Pipe(pipe)));
+        let result = self.execute_tested(command, context);
+        if let Some(stdin) = saved_stdin {
+            context.stdin = stdin;
+        }
+        if let Some(input) = saved_input {
+            self.input = input;
+        }
+        if let Some(stdout) = saved_stdout {
+            context.stdout = stdout;
+        }
+        if let Some(output) = saved_output {
+            self.output = output;
+        }
+
+        let Some(mut writer) = writer else {
+            return result;
+        };
+        // Output handed back rather than written follows it down the pipe
+        let result = result.map(|mut result| {
+            let _ = writer.write_all(result.stdout.as_bytes());
+            result.stdout.clear();
+            result
+        });
+        if writer.is_broken() {
+            // 128 + SIGPIPE
+            return Ok(ExecutionResult::failure(141));
+        }
+        result
+    }
+
+    /// The stages of a structured pipeline: every element but the first is a
+    /// structured builtin, and the first is one too or an ordinary command
+    /// whose output the second stage reads as a string
+    fn structured_stages<'n, 'src>(
+        &self,
+        commands: &'n [AstNode<'src>],
+        context: &ShellContext,
+    ) -> Option<StructuredStages<'n, 'src>> {
+        if commands.len() < 2 || self.structured_builtins.is_empty() {
+            return None;
+        }
+        let (first, rest) = commands.split_first()?;
+        let mut stages = rest
+            .iter()
+            .map(|command| self.structured_stage(command, context))
+            .collect::<Option<Vec<_>>>()?;
+        match self.structured_stage(first, context) {
+            Some(stage) => {
+                stages.insert(0, stage);
+                Some((None, stages))
+            }
+            None => Some((Some(first), stages)),
+        }
+    }
+
+    /// The structured builtin and argument nodes of a pipeline element that
+    /// runs one, without redirections and not hidden by a function
+    fn structured_stage<'n, 'src>(
+        &self,
+        command: &'n AstNode<'src>,
+        context: &ShellContext,
+    ) -> Option<StructuredStage<'n, 'src>> {
+        let AstNode::Command {
+            name,
+            args,
+            redirections,
+            background: false,
+        } = command
+        else {
+            return None;
+        };
+        let name = match name.as_ref() {
+            AstNode::Word(word) => *word,
+            AstNode::StringLiteral { value, .. } => *value,
+            _ => return None,
+        };
+        if !redirections.is_empty() || context.has_function(name) {
+            return None;
+        }
+        let builtin = self.structured_builtins.get(name)?;
+        Some((Arc::clone(builtin), args.as_slice()))
+    }
+
+    /// Run a pipeline of structured builtins, handing each stage's value to
+    /// the next. A leading ordinary command runs like a command substitution
+    /// and its output becomes the first value. The last value is drawn as a
+    /// table when it goes straight to a terminal, and written as plain text
+    /// lines otherwise.
+    fn execute_structured_pipeline(
+        &mut self,
+        (source, stages): StructuredStages<'_, '_>,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        use std::io::IsTerminal;
+
+        let start_time = Instant::now();
+        let mut stderr = String::new();
+        let mut data: Option<PipelineData> = None;
+        if let Some(source) = source {
+            let result = self.eval_cmd_substitution(source, context)?;
+            stderr.push_str(&result.stderr);
+            data = Some(PipelineData::new(StructuredValue::String(result.stdout)));
+        }
+        for (builtin, args) in &stages {
+            if context.is_timed_out() {
+                stderr.push_str("nxsh: pipeline timed out\n");
+                return Ok(ExecutionResult::failure(124).with_error(stderr.into_bytes()));
+            }
+            let args = self.expand_command_args(args, context)?;
+            let columns = data.as_ref().map(PipelineData::columns);
+            let mut output = match builtin.run(context, &args, data.take()) {
+                Ok(output) => output,
+                Err(e) => {
+                    stderr.push_str(&format!("nxsh: {}: {}\n", builtin.name(), e.message));
+                    return Ok(ExecutionResult::failure(1).with_error(stderr.into_bytes()));
+                }
+            };
+            // Stages that only filter or reorder rows keep the column order
+            if let Some(columns) = columns {
+                if !output.metadata.contains_key(PipelineData::COLUMNS) {
+                    output = output.with_columns(&columns);
+                }
+            }
+            data = Some(output);
+        }
+
+        let data = data.unwrap_or_else(|| PipelineData::new(StructuredValue::Nothing));
+        let to_terminal = self.output.is_inherit() && std::io::stdout().is_terminal();
+        let stdout = match (to_terminal, self.table_renderer) {
+            (true, Some(render)) if data.is_tabular() => {
+                let (headers, rows) = data.cells();
+                let mut table = render(&headers, &rows);
+                if !table.ends_with('\n') {
+                    table.push('\n');
+                }
+                table
+            }
+            _ => data.to_text(),
+        };
+        let execution_time = start_time.elapsed().as_micros() as u64;
+        Ok(ExecutionResult {
+            exit_code: 0,
+            stdout,
+            stderr,
+            execution_time,
+            strategy: ExecutionStrategy::DirectInterpreter,
+            metrics: ExecutionMetrics {
+                execute_time_us: execution_time,
+                instruction_count: stages.len() as u64,
+                ..ExecutionMetrics::default()
+            },
+        })
+    }
+
+    /// Execute a node whose exit status is being tested, so a failure there
+    /// does not fire the ERR trap
+    fn execute_tested(
+        &mut self,
+        node: &AstNode,
+        context: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        self.condition_depth += 1;
+        let result = self.execute_ast_direct(node, context);
+        self.condition_depth -= 1;
+        result
+    }
+
+    /// Value of `$name`; an unset variable is an error under `set -u`
+    fn expand_variable(name: &str, context: &ShellContext) -> ShellResult<String> {
+        if name.len() > 1 {
+            if let Some(target) = name.strip_prefix('#') {
+                // `${#a[@]}` counts elements, `${#name}` measures the value
+                if target == "@" || target == "*" {
+                    return Ok(context.positional_args().len().to_string());
+                }
+                if let Some((array, "@" | "*")) = split_subscript(target) {
+                    let count = match context.get_array(array) {
+                        Some(array) => array.len(),
+                        None => usize::from(context.get_var(array).is_some()),
+                    };
+                    return Ok(count.to_string());
+                }
+                let value = Self::expand_variable(target, context)?;
+                return Ok(value.chars().count().to_string());
+            }
+            if let Some(target) = name.strip_prefix('!') {
+                if let Some((array, "@" | "*")) = split_subscript(target) {
+                    let keys = match context.get_array(array) {
+                        Some(array) => array.keys(),
+                        None if context.get_var(array).is_some() => vec!["0".to_string()],
+                        None => Vec::new(),
+                    };
+                    return Ok(keys.join(" "));
+                }
+                // `${!ref}` expands the variable whose name `ref` holds
+                let referenced = context.get_var(target).unwrap_or_default();
+                if referenced.is_empty() {
+                    return Ok(String::new());
+                }
+                return Self::expand_variable(&referenced, context);
+            }
+        }
+        match context.get_var(name) {
+            Some(value) => Ok(value),
+            None if context.get_option("nounset").unwrap_or(false)
+                && name != "@"
+                && name != "*" =>
+            {
+                Err(ShellError::new(
+                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::VariableNotFound),
+                    format!("{name}: unbound variable"),
+                ))
+            }
+            None => Ok(String::new()),
+        }
+    }
+
+    /// Fields produced by an unquoted parameter: `$@`, `$*`, `${a[@]}`,
+    /// `${a[*]}` and `${!a[@]}` give one field per element, anything else a
+    /// single field
+    fn expand_variable_fields(name: &str, context: &ShellContext) -> ShellResult<Vec<String>> {
+        if name == "@" || name == "*" {
+            return Ok(context.positional_args());
+        }
+        let (keys, target) = match name.strip_prefix('!') {
+            Some(target) => (true, target),
+            None => (false, name),
+        };
+        if let Some((array, "@" | "*")) = split_subscript(target) {
+            let fields = match context.get_array(array) {
+                Some(array) if keys => array.keys(),
+                Some(array) => array.values(),
+                None => match context.get_var(array) {
+                    Some(_) if keys => vec!["0".to_string()],
+                    Some(value) => vec![value],
+                    None => Vec::new(),
+                },
+            };
+            return Ok(fields);
+        }
+        Ok(vec![Self::expand_variable(name, context)?])
+    }
+
+    /// `${#name}` arrives as `name` with a `Length` modifier; fold it back
+    /// into the `#name` form understood by `expand_variable`
+    fn parameter_expr<'a>(
+        name: &'a str,
+        modifier: &Option<nxsh_parser::ast::ParameterModifier>,
+    ) -> std::borrow::Cow<'a, str> {
+        match modifier {
+            Some(nxsh_parser::ast::ParameterModifier::Length) => format!("#{name}").into(),
+            _ => name.into(),
         }
     }
 
-    /// (test helper) Evaluate args expansion & splitting like execute_command would
-    #[cfg(test)]
-    #[allow(dead_code)]
-    pub(crate) fn evaluate_args_for_test(
-        &mut self,
-        args: &[AstNode],
-        context: &mut ShellContext,
-    ) -> Vec<String> {
-        fn split_fields(raw: &str, context: &ShellContext) -> Vec<String> {
-            if context.get_var("NXSH_SUBST_SPLIT").as_deref() != Some("1") {
-                return vec![raw.to_string()];
-            }
-            let ifs = context
-                .get_var("NXSH_IFS")
-                .unwrap_or_else(|| " \t\n".to_string());
-            let mut out = Vec::new();
-            let mut current = String::new();
-            for ch in raw.chars() {
-                if ifs.contains(ch) {
-                    if !current.is_empty() {
-                        out.push(std::mem::take(&mut current));
+    /// Expand the text of an assignment value or array element. Quotes are
+    /// removed and `$name`, `${...}` and special parameters are substituted
+    /// (except inside single quotes); the result is never field-split.
+    fn expand_value_text(text: &str, context: &ShellContext) -> ShellResult<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut in_single = false;
+        let mut in_double = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            i += 1;
+            match c {
+                '\'' if !in_double => in_single = !in_single,
+                '"' if !in_single => in_double = !in_double,
+                '\\' if !in_single
+                    && i < chars.len()
+                    && (!in_double || "$`\"\\".contains(chars[i])) =>
+                {
+                    out.push(chars[i]);
+                    i += 1;
+                }
+                '$' if !in_single && i < chars.len() => {
+                    let next = chars[i];
+                    if next == '{' {
+                        let Some(len) = chars[i + 1..].iter().position(|&ch| ch == '}') else {
+                            out.push('$');
+                            continue;
+                        };
+                        let expr: String = chars[i + 1..i + 1 + len].iter().collect();
+                        out.push_str(&Self::expand_variable(&expr, context)?);
+                        i += len + 2;
+                    } else if next.is_ascii_alphabetic() || next == '_' {
+                        let len = chars[i..]
+                            .iter()
+                            .position(|&ch| !(ch.is_ascii_alphanumeric() || ch == '_'))
+                            .unwrap_or(chars.len() - i);
+                        let name: String = chars[i..i + len].iter().collect();
+                        out.push_str(&Self::expand_variable(&name, context)?);
+                        i += len;
+                    } else if next.is_ascii_digit() || "@*#?$!-".contains(next) {
+                        out.push_str(&Self::expand_variable(&next.to_string(), context)?);
+                        i += 1;
+                    } else {
+                        out.push('$');
                     }
-                } else {
-                    current.push(ch);
                 }
+                _ => out.push(c),
             }
-            if !current.is_empty() {
-                out.push(current);
-            }
-            if out.is_empty() {
-                vec![String::new()]
+        }
+        Ok(out)
+    }
+
+    /// `name=value` / `name+=value`; `name` may carry a subscript (`a[1]`).
+    /// Assigning to an array without a subscript sets element 0.
+    fn assign_scalar(
+        name: &str,
+        value: String,
+        append: bool,
+        context: &ShellContext,
+    ) -> ExecutionResult {
+        let (target, subscript) = split_subscript(name).unwrap_or((name, "0"));
+        let is_element = target != name || context.has_array(target);
+        let value = if append {
+            let current = if is_element {
+                context.get_array_element(target, subscript)
             } else {
-                out
-            }
+                context.get_var(target)
+            };
+            current.unwrap_or_default() + &value
+        } else {
+            value
+        };
+        if !is_element {
+            context.set_var(target.to_string(), value);
+            return ExecutionResult::success(0);
         }
-        let mut evaluated = Vec::new();
-        for arg in args {
-            match arg {
-                AstNode::Word(s) => evaluated.push(s.to_string()),
-                AstNode::StringLiteral { value, .. } => evaluated.push(value.to_string()),
-                AstNode::NumberLiteral { value, .. } => evaluated.push(value.to_string()),
-                AstNode::VariableExpansion { name, .. } => {
-                    evaluated.push(context.get_var(name).unwrap_or_else(|| name.to_string()))
-                }
-                AstNode::CommandSubstitution { command, is_legacy } => {
-                    match self.eval_cmd_substitution(command, context) {
-                        Ok(r) => {
-                            let mut merged = r.stdout;
-                            let stderr_mode = context
-                                .get_var("NXSH_SUBST_STDERR")
-                                .unwrap_or_else(|| "separate".to_string());
-                            if stderr_mode.eq_ignore_ascii_case("merge") && !r.stderr.is_empty() {
-                                if !merged.is_empty() && !merged.ends_with('\n') {
-                                    merged.push('\n');
-                                }
-                                merged.push_str(&r.stderr);
-                            }
-                            let trimmed = merged.trim_end();
-                            let should_split = (*is_legacy)
-                                || context.get_var("NXSH_SUBST_SPLIT").as_deref() == Some("1");
-                            if should_split {
-                                for part in split_fields(trimmed, context) {
-                                    evaluated.push(part);
-                                }
-                            } else {
-                                evaluated.push(trimmed.to_string());
-                            }
-                        }
-                        Err(_) => evaluated.push(String::new()),
-                    }
-                }
-                _ => evaluated.push(format!("{arg:?}")),
-            }
+        match context.set_array_element(target, subscript, value) {
+            Ok(()) => ExecutionResult::success(0),
+            Err(e) => ExecutionResult::failure(1).with_error(format!("nxsh: {e}\n").into_bytes()),
         }
-        evaluated
     }
 
-    /// Execute a pipeline of commands
-    fn execute_pipeline(
+    /// `name=(elem ...)` / `name+=(elem ...)`. An associative array declared
+    /// with `declare -A` keeps its type; `[key]=value` elements set explicit
+    /// indices or keys and the rest are appended in order.
+    fn assign_array(
         &mut self,
-        commands: &[AstNode],
+        name: &str,
+        elements: &[nxsh_parser::ast::ArrayElement],
+        append: bool,
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        let _start_time = Instant::now();
-        // Fast path on Windows: delegate to cmd.exe to get real pipe semantics across externals/builtins
-        #[cfg(windows)]
-        {
-            use std::process::Command;
-            // Helper to serialize a simple Command node into a shell-friendly string
-            fn cmd_to_string(cmd: &AstNode) -> Option<String> {
-                if let AstNode::Command { name, args, .. } = cmd {
-                    let name_str = match name.as_ref() {
-                        AstNode::Word(w) => w.to_string(),
-                        AstNode::StringLiteral { value, .. } => value.to_string(),
-                        _ => return None,
-                    };
-                    let mut parts = vec![name_str];
-                    for a in args {
-                        match a {
-                            AstNode::Word(w) => parts.push(w.to_string()),
-                            AstNode::StringLiteral { value, .. } => {
-                                // Quote with simple CMD-safe quoting: double inner quotes, then wrap
-                                let s = value.replace('"', "\"\"");
-                                parts.push(format!("\"{s}\""));
-                            }
-                            AstNode::NumberLiteral { value, .. } => parts.push(value.to_string()),
-                            AstNode::VariableExpansion { name, .. } => {
-                                parts.push(format!("%{name}%"))
-                            }
-                            _ => parts.push(format!("{a:?}")),
-                        }
+        let existing = context.get_array(name);
+        let base = match existing {
+            Some(array) if append => array,
+            Some(array) if array.is_associative() => ShellArray::new_associative(),
+            _ if append => ShellArray::from_values(context.get_var(name)),
+            _ => ShellArray::from_values(None),
+        };
+        let associative = base.is_associative();
+        context.set_array(name, base);
+        for element in elements {
+            // `a=($(cmd))` adds one element per field of the output
+            if let (AstNode::Word(text), None) = (&element.value, &element.index) {
+                if !associative && is_bare_substitution(text) {
+                    let output = self.expand_word_text(text, context)?;
+                    for field in Self::split_ifs(&output, context) {
+                        context.push_array_element(name, field);
                     }
-                    Some(parts.join(" "))
-                } else {
-                    None
-                }
-            }
-            let mut serialized: Vec<String> = Vec::new();
-            for c in commands {
-                if let Some(s) = cmd_to_string(c) {
-                    serialized.push(s);
-                } else {
-                    serialized.clear();
-                    break;
+                    continue;
                 }
             }
-            if !serialized.is_empty() && serialized.len() >= 2 {
-                let pipeline_str = serialized.join(" | ");
-                let start_time = Instant::now();
-                let mut cmd = Command::new("cmd.exe");
-                cmd.args(["/C", &pipeline_str]);
-                // Propagate env and cwd
-                if let Ok(env) = context.env.read() {
-                    for (k, v) in env.iter() {
-                        cmd.env(k, v);
-                    }
+            let value = match &element.value {
+                AstNode::Word(text) => self.expand_word_text(text, context)?,
+                other => simple_unparse(other),
+            };
+            let result = match &element.index {
+                Some(index) => {
+                    let subscript = self.expand_word_text(&simple_unparse(index), context)?;
+                    context.set_array_element(name, &subscript, value)
                 }
-                cmd.current_dir(&context.cwd);
-                // Execute with optional timeout using wait_timeout like external process path
-                use wait_timeout::ChildExt;
-                let mut child = cmd.spawn().map_err(|e| {
-                    ShellError::new(
-                        ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                        format!("Failed to execute pipeline: {e}"),
-                    )
-                })?;
-                let output = if let Some(dur) = context.per_command_timeout() {
-                    match child.wait_timeout(dur).map_err(|e| {
-                        ShellError::new(
-                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                            format!("Process wait error: {e}"),
-                        )
-                    })? {
-                        Some(_status) => child.wait_with_output().map_err(|e| {
-                            ShellError::new(
-                                ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                                format!("Process output error: {e}"),
-                            )
-                        })?,
-                        None => {
-                            let _ = child.kill();
-                            return Ok(ExecutionResult {
-                                exit_code: 124,
-                                stdout: String::new(),
-                                stderr: "nxsh: pipeline timed out".to_string(),
-                                execution_time: start_time.elapsed().as_micros() as u64,
-                                strategy: ExecutionStrategy::DirectInterpreter,
-                                metrics: ExecutionMetrics::default(),
-                            });
-                        }
-                    }
-                } else {
-                    child.wait_with_output().map_err(|e| {
-                        ShellError::new(
-                            ErrorKind::SystemError(crate::error::SystemErrorKind::ProcessError),
-                            format!("Process output error: {e}"),
+                None if associative => {
+                    return Ok(ExecutionResult::failure(1).with_error(
+                        format!(
+                            "nxsh: {name}: {value}: must use subscript when assigning associative array\n"
                         )
-                    })?
-                };
-                let execution_time = start_time.elapsed().as_micros() as u64;
-                return Ok(ExecutionResult {
-                    exit_code: output.status.code().unwrap_or(-1),
-                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    execution_time,
-                    strategy: ExecutionStrategy::DirectInterpreter,
-                    metrics: ExecutionMetrics {
-                        execute_time_us: execution_time,
-                        ..Default::default()
-                    },
-                });
+                        .into_bytes(),
+                    ));
+                }
+                None => {
+                    context.push_array_element(name, value);
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                return Ok(
+                    ExecutionResult::failure(1).with_error(format!("nxsh: {e}\n").into_bytes())
+                );
             }
         }
-        // Experimental: if PowerShell compatibility requested, attempt object pipeline using simplified textual reconstruction
-        if std::env::var("NXSH_PWSH_MODE").ok().as_deref() == Some("1") {
-            #[cfg(feature = "powershell_compat")]
-            {
-                let mut parts = Vec::new();
-                for c in commands.iter() {
-                    parts.push(format!("{c:?}"));
-                }
-                let pipeline_str = parts.join(" | ");
-                let mut compat = crate::powershell_compat::PowerShellCompat::new();
-                if let Ok(objs) = compat.execute_pipeline(&pipeline_str) {
-                    let out = objs
-                        .iter()
-                        .map(|o| o.to_string())
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    let execution_time = _start_time.elapsed().as_micros() as u64;
-                    return Ok(ExecutionResult {
-                        exit_code: 0,
-                        stdout: out,
-                        stderr: String::new(),
-                        execution_time,
-                        strategy: ExecutionStrategy::DirectInterpreter,
-                        metrics: ExecutionMetrics {
-                            execute_time_us: execution_time,
-                            ..Default::default()
-                        },
+        Ok(ExecutionResult::success(0))
+    }
+
+    /// `set -x` trace line for a command: `$PS4` followed by the expanded
+    /// words, quoted where needed so the line can be re-read by the shell
+    pub fn format_trace(name: &str, args: &[String], context: &ShellContext) -> String {
+        let mut line = context.get_var("PS4").unwrap_or_else(|| "+ ".to_string());
+        for (i, word) in std::iter::once(name)
+            .chain(args.iter().map(String::as_str))
+            .enumerate()
+        {
+            if i > 0 {
+                line.push(' ');
+            }
+            line.push_str(&Self::shell_quote(word));
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Single-quote `word` when the shell would otherwise reinterpret it
+    fn shell_quote(word: &str) -> std::borrow::Cow<'_, str> {
+        let needs_quotes = word.is_empty()
+            || word.contains(|c: char| c.is_whitespace() || "'\"$`\\;&|<>*?()[]".contains(c));
+        if needs_quotes {
+            format!("'{}'", word.replace('\'', "'\\''")).into()
+        } else {
+            word.into()
+        }
+    }
+
+    /// Render an assignment passed as a command argument (`declare a=(x y)`,
+    /// `export A=1`) as a single expanded word. Array elements are quoted so
+    /// the receiving builtin can split them again.
+    fn assignment_word(
+        &mut self,
+        node: &AstNode,
+        context: &mut ShellContext,
+    ) -> ShellResult<String> {
+        match node {
+            AstNode::VariableAssignment {
+                name,
+                operator,
+                value,
+                ..
+            } => {
+                let op = match operator {
+                    nxsh_parser::ast::AssignmentOperator::AddAssign => "+=",
+                    _ => "=",
+                };
+                let value = match value.as_ref() {
+                    AstNode::Word(text) => self.expand_word_text(text, context)?,
+                    other => simple_unparse(other),
+                };
+                Ok(format!("{name}{op}{value}"))
+            }
+            AstNode::ArrayAssignment {
+                name,
+                operator,
+                elements,
+                ..
+            } => {
+                let op = match operator {
+                    nxsh_parser::ast::AssignmentOperator::AddAssign => "+=",
+                    _ => "=",
+                };
+                let mut words = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let value = match &element.value {
+                        AstNode::Word(text) => self.expand_word_text(text, context)?,
+                        other => simple_unparse(other),
+                    };
+                    let value = Self::shell_quote(&value).into_owned();
+                    words.push(match &element.index {
+                        Some(index) => {
+                            let key = Self::expand_value_text(&simple_unparse(index), context)?;
+                            format!("[{key}]={value}")
+                        }
+                        None => value,
                     });
                 }
+                Ok(format!("{name}{op}({})", words.join(" ")))
             }
+            other => Ok(format!("{other:?}")),
         }
-        let mut final_result = ExecutionResult {
-            exit_code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-            execution_time: 0,
-            strategy: ExecutionStrategy::DirectInterpreter,
-            metrics: ExecutionMetrics::default(),
+    }
+
+    /// Run the trap registered for `condition`, if any.
+    ///
+    /// Traps never fire from inside another trap, and `$?` seen after the
+    /// trap is the status from before it ran.
+    pub fn run_trap(
+        &mut self,
+        condition: TrapCondition,
+        context: &mut ShellContext,
+    ) -> ShellResult<Option<ExecutionResult>> {
+        if self.trap_depth > 0 {
+            return Ok(None);
+        }
+        let action = match context.traps.read() {
+            Ok(traps) => traps.action(condition).map(str::to_string),
+            Err(_) => None,
+        };
+        let Some(action) = action else {
+            return Ok(None);
         };
+        let ast = parse_program(&action).map_err(|e| {
+            ShellError::new(
+                ErrorKind::ParseError(crate::error::ParseErrorKind::SyntaxError),
+                format!("trap {}: {e}", condition.name()),
+            )
+        })?;
 
-        for command in commands {
-            if context.is_timed_out() {
-                final_result.exit_code = 124;
-                final_result.stderr = "nxsh: pipeline timed out".to_string();
-                break;
+        let saved_status = context.get_exit_status();
+        self.trap_depth += 1;
+        let result = self.execute_ast_direct(&ast, context);
+        self.trap_depth -= 1;
+        context.set_exit_status(saved_status);
+        result.map(Some)
+    }
+
+    /// Run the hooks on `event` in the order they were added, returning
+    /// their combined output. Hook code sees the event in `HOOK_*`
+    /// variables and leaves `$?` alone; a hook that fails is reported on
+    /// stderr and the rest still run.
+    pub fn run_hooks(&mut self, event: &HookEvent, context: &mut ShellContext) -> ExecutionResult {
+        let mut output = ExecutionResult::success(0);
+        if self.hook_depth > 0 {
+            return output;
+        }
+        let hooks = context.hooks.list(Some(event.kind()));
+        if hooks.is_empty() {
+            return output;
+        }
+        let values = event.values();
+        let saved_status = context.get_exit_status();
+        self.hook_depth += 1;
+        for hook in hooks {
+            let result = match &hook.action {
+                HookAction::Script(code) => {
+                    context.set_var("HOOK_EVENT", event.kind().name());
+                    for (name, value) in &values {
+                        context.set_var(*name, value.as_str());
+                    }
+                    parse_program(code)
+                        .map_err(|e| {
+                            ShellError::new(
+                                ErrorKind::ParseError(crate::error::ParseErrorKind::SyntaxError),
+                                e.to_string(),
+                            )
+                        })
+                        .and_then(|ast| self.execute_ast_direct(&ast, context))
+                }
+                HookAction::Callback { run, .. } => run(event, context),
+            };
+            match result {
+                Ok(result) => Self::merge_trap_output(&mut output, Some(result)),
+                Err(e) => output
+                    .stderr
+                    .push_str(&format!("nxsh: {} hook: {e}\n", event.kind())),
             }
-            let result = self.execute_ast_direct(command, context)?;
-            final_result.execution_time += result.execution_time;
-            final_result.stdout = result.stdout;
-            if result.exit_code != 0 {
-                final_result.exit_code = result.exit_code;
-                final_result.stderr = result.stderr;
-                break;
+        }
+        context.unset_var("HOOK_EVENT");
+        for (name, _) in &values {
+            context.unset_var(name);
+        }
+        self.hook_depth -= 1;
+        self.errexit_pending = false;
+        self.return_pending = false;
+        context.set_exit_status(saved_status);
+        output
+    }
+
+    /// Run traps for every signal delivered since the last check
+    pub fn run_pending_signal_traps(
+        &mut self,
+        context: &mut ShellContext,
+    ) -> ShellResult<Option<ExecutionResult>> {
+        if self.trap_depth > 0 || !nxsh_hal::signal::has_pending() {
+            return Ok(None);
+        }
+        let mut combined: Option<ExecutionResult> = None;
+        for sig in nxsh_hal::signal::take_pending() {
+            let trap = self.run_trap(TrapCondition::Signal(sig), context)?;
+            match (&mut combined, trap) {
+                (Some(acc), Some(next)) => {
+                    acc.stdout.push_str(&next.stdout);
+                    acc.stderr.push_str(&next.stderr);
+                }
+                (None, next) => combined = next,
+                (Some(_), None) => {}
             }
         }
+        Ok(combined)
+    }
 
-        Ok(final_result)
+    /// Run the EXIT trap once; it is cleared so it cannot fire twice
+    pub fn run_exit_trap(
+        &mut self,
+        context: &mut ShellContext,
+    ) -> ShellResult<Option<ExecutionResult>> {
+        let result = self.run_trap(TrapCondition::Exit, context)?;
+        if let Ok(mut traps) = context.traps.write() {
+            traps.reset(TrapCondition::Exit)?;
+        }
+        Ok(result)
+    }
+
+    /// Append a trap's output to the result it interrupted
+    fn merge_trap_output(result: &mut ExecutionResult, trap: Option<ExecutionResult>) {
+        if let Some(trap) = trap {
+            result.stdout.push_str(&trap.stdout);
+            result.stderr.push_str(&trap.stderr);
+        }
     }
 
     /// Execute a conditional statement
@@ -3679,7 +6359,7 @@ impl Executor {
         else_branch: Option<&AstNode>,
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        let condition_result = self.execute_ast_direct(condition, context)?;
+        let condition_result = self.execute_tested(condition, context)?;
 
         if condition_result.exit_code == 0 {
             self.execute_ast_direct(then_branch, context)
@@ -3697,458 +6377,352 @@ impl Executor {
         }
     }
 
-    /// Execute a loop
-    #[allow(dead_code)]
+    /// Execute a `while` (or, with `until` set, an `until`) loop
     fn execute_loop(
         &mut self,
         condition: &AstNode,
         body: &AstNode,
+        until: bool,
         context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
         let mut total_time = 0;
-        let mut last_result = ExecutionResult {
-            exit_code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-            execution_time: 0,
-            strategy: ExecutionStrategy::DirectInterpreter,
-            metrics: ExecutionMetrics::default(),
-        };
+        let mut last_result = ExecutionResult::success(0);
+        let mut stdout = String::new();
+        let mut stderr = String::new();
 
         loop {
-            let condition_result = self.execute_ast_direct(condition, context)?;
+            if context.is_timed_out() {
+                return Ok(ExecutionResult {
+                    exit_code: 124,
+                    stdout,
+                    stderr: stderr + "nxsh: execution timed out",
+                    execution_time: total_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics::default(),
+                });
+            }
+
+            let condition_result = self.execute_tested(condition, context)?;
             total_time += condition_result.execution_time;
+            stdout.push_str(&condition_result.stdout);
+            stderr.push_str(&condition_result.stderr);
 
-            if condition_result.exit_code != 0 {
+            let keep_going = (condition_result.exit_code == 0) != until;
+            if !keep_going {
                 break;
             }
 
             let body_result = self.execute_ast_direct(body, context)?;
             total_time += body_result.execution_time;
+            stdout.push_str(&body_result.stdout);
+            stderr.push_str(&body_result.stderr);
             last_result = body_result;
 
-            // Simple loop protection
-            if total_time > 10_000_000 {
-                // 10 seconds
-                return Err(ShellError::new(
-                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::Timeout),
-                    "Loop execution timeout",
-                ));
+            let signal_traps = self.run_pending_signal_traps(context)?;
+            if let Some(trap) = signal_traps {
+                stdout.push_str(&trap.stdout);
+                stderr.push_str(&trap.stderr);
+            }
+
+            if self.unwinding() {
+                break;
+            }
+            if context.should_break() {
+                context.clear_break();
+                break;
+            }
+            if context.should_continue() {
+                context.clear_continue();
             }
         }
 
+        last_result.stdout = stdout;
+        last_result.stderr = stderr;
         last_result.execution_time = total_time;
         Ok(last_result)
     }
 
-    /// Get executor statistics
-    pub fn stats(&self) -> &ExecutorStats {
-        &self.stats
-    }
-
-    /// Reset executor statistics
-    pub fn reset_stats(&mut self) {
-        self.stats = ExecutorStats::default();
-    }
-
-    /// Execute subshell with complete isolation
-    fn execute_subshell(
+    /// Execute a C-style `for ((init; cond; update))` loop
+    fn execute_arith_for(
         &mut self,
-        commands: &[AstNode],
-        ctx: &mut ShellContext,
+        init: Option<&AstNode>,
+        condition: Option<&AstNode>,
+        update: Option<&AstNode>,
+        body: &AstNode,
+        context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        // Check if process isolation is enabled
-        let enable_isolation = ctx.get_option("enable_process_isolation").unwrap_or(true);
+        let mut total_time = 0;
+        let mut last_result = ExecutionResult::success(0);
+        let mut stdout = String::new();
+        let mut stderr = String::new();
 
-        if enable_isolation {
-            // Execute in completely isolated process
-            self.execute_subshell_isolated(commands, ctx)
-        } else {
-            // Execute in same process with context isolation
-            self.execute_subshell_local(commands, ctx)
+        if let Some(init) = init {
+            Self::eval_arith_node(init, context)?;
         }
-    }
 
-    /// Execute subshell in isolated process (fork-like behavior)
-    fn execute_subshell_isolated(
-        &mut self,
-        commands: &[AstNode],
-        ctx: &mut ShellContext,
-    ) -> ShellResult<ExecutionResult> {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-        use tempfile::NamedTempFile;
-        let start_time = std::time::Instant::now();
+        loop {
+            if context.is_timed_out() {
+                return Ok(ExecutionResult {
+                    exit_code: 124,
+                    stdout,
+                    stderr: stderr + "nxsh: execution timed out",
+                    execution_time: total_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics::default(),
+                });
+            }
 
-        // Create temporary script file for subshell execution
-        let mut temp_script = NamedTempFile::new().map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                format!("Failed to create temporary script: {e}"),
-            )
-        })?;
+            // An omitted condition is treated as always true
+            if let Some(cond) = condition {
+                if Self::eval_arith_node(cond, context)? == 0 {
+                    break;
+                }
+            }
 
-        // Convert commands to script text
-        let script_content = self.commands_to_script(commands)?;
-        temp_script
-            .write_all(script_content.as_bytes())
-            .map_err(|e| {
-                ShellError::new(
-                    ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                    format!("Failed to write script: {e}"),
-                )
-            })?;
-        temp_script.flush().map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                format!("Failed to flush script: {e}"),
-            )
-        })?;
+            let body_result = self.execute_ast_direct(body, context)?;
+            total_time += body_result.execution_time;
+            stdout.push_str(&body_result.stdout);
+            stderr.push_str(&body_result.stderr);
+            last_result = body_result;
 
-        // Prepare environment variables for subshell
-        let subshell_env = self.prepare_subshell_environment(ctx)?;
+            if self.unwinding() {
+                break;
+            }
+            if context.should_break() {
+                context.clear_break();
+                break;
+            }
+            if context.should_continue() {
+                context.clear_continue();
+            }
 
-        // Execute subshell as separate process
-        let child = Command::new(std::env::current_exe().map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                format!("Failed to get current executable: {e}"),
-            )
-        })?)
-        .arg("--subshell")
-        .arg(temp_script.path())
-        .envs(&subshell_env)
-        .current_dir(&ctx.cwd)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                format!("Failed to spawn subshell process: {e}"),
-            )
-        })?;
+            if let Some(update) = update {
+                Self::eval_arith_node(update, context)?;
+            }
+        }
 
-        // Wait for completion and collect output
-        let output = child.wait_with_output().map_err(|e| {
-            ShellError::new(
-                ErrorKind::IoError(crate::error::IoErrorKind::Other),
-                format!("Failed to wait for subshell: {e}"),
-            )
-        })?;
-        let execution_time = start_time.elapsed().as_micros() as u64;
+        last_result.stdout = stdout;
+        last_result.stderr = stderr;
+        last_result.execution_time = total_time;
+        Ok(last_result)
+    }
 
-        Ok(ExecutionResult {
-            exit_code: output.status.code().unwrap_or(-1),
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-            execution_time,
-            strategy: ExecutionStrategy::DirectInterpreter,
-            metrics: ExecutionMetrics {
-                execute_time_us: execution_time,
-                ..Default::default()
-            },
-        })
+    /// Evaluate an arithmetic AST node (raw expression text) to an integer
+    fn eval_arith_node(node: &AstNode, context: &ShellContext) -> ShellResult<i64> {
+        match node {
+            AstNode::ArithmeticExpansion { expr, .. } => Self::eval_arith_node(expr, context),
+            AstNode::Word(text) => crate::arithmetic::evaluate(text, context),
+            AstNode::NumberLiteral { value, .. } => crate::arithmetic::evaluate(value, context),
+            other => crate::arithmetic::evaluate(&simple_unparse(other), context),
+        }
     }
 
-    /// Execute subshell with local context isolation (in-process)
-    fn execute_subshell_local(
+    /// Execute a `select name in words; do ...; done` menu loop
+    ///
+    /// The menu and `PS3` prompt are drawn by the menu renderer, if one is
+    /// set, and go to stderr like bash. Each line read from
+    /// stdin is stored in `REPLY`; a valid number assigns the matching word to
+    /// the loop variable (otherwise it is set to the empty string) and runs the
+    /// body. The loop ends on EOF or `break`.
+    fn execute_select(
         &mut self,
-        commands: &[AstNode],
-        ctx: &mut ShellContext,
+        variable: &str,
+        options: Option<&AstNode>,
+        body: &AstNode,
+        context: &mut ShellContext,
     ) -> ShellResult<ExecutionResult> {
-        // Create isolated context by deep-cloning the current context
-        let mut subshell_ctx = self.create_isolated_context(ctx)?;
+        use std::io::{Read, Write};
 
-        // Increment subshell level
-        {
-            let mut options = subshell_ctx.options.write().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to acquire options lock",
-                )
-            })?;
-            options.subshell_level += 1;
+        let items: Vec<String> = match options {
+            Some(AstNode::ArgumentList(list)) => self.evaluate_args(list, context),
+            Some(node) => self.evaluate_args(std::slice::from_ref(node), context),
+            None => Self::positional_params(context),
+        };
+        if items.is_empty() {
+            return Ok(ExecutionResult::success(0));
         }
 
-        // Execute commands in isolated context
-        let result = self.execute_pipeline(commands, &mut subshell_ctx)?;
-
-        // Subshell changes do NOT affect parent context
-        // (variables, functions, aliases remain isolated)
-
-        Ok(result)
-    }
-
-    /// Create completely isolated context for subshell
-    fn create_isolated_context(&self, parent_ctx: &ShellContext) -> ShellResult<ShellContext> {
-        // Create new context with deep-copied state
-        let mut isolated_ctx = ShellContext::new();
-
-        // Copy exported environment variables only
-        {
-            let parent_vars = parent_ctx.vars.read().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to read parent variables",
-                )
-            })?;
-            let parent_env = parent_ctx.env.read().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to read parent environment",
-                )
-            })?;
+        let mut last_result = ExecutionResult::success(0);
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let mut show_menu = true;
 
-            let mut isolated_env = isolated_ctx.env.write().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to acquire isolated environment lock",
-                )
-            })?;
-            let mut isolated_vars = isolated_ctx.vars.write().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to acquire isolated variables lock",
-                )
-            })?;
+        loop {
+            if context.is_timed_out() {
+                return Ok(ExecutionResult {
+                    exit_code: 124,
+                    stdout,
+                    stderr: stderr + "nxsh: execution timed out",
+                    execution_time: last_result.execution_time,
+                    strategy: ExecutionStrategy::DirectInterpreter,
+                    metrics: ExecutionMetrics::default(),
+                });
+            }
 
-            // Copy exported variables to subshell environment
-            for (key, var) in parent_vars.iter() {
-                if var.exported {
-                    isolated_env.insert(key.clone(), var.value.clone());
-                    isolated_vars.insert(key.clone(), var.clone());
+            let prompt = context.get_var("PS3").unwrap_or_else(|| "#? ".to_string());
+            let columns = context
+                .get_var("COLUMNS")
+                .and_then(|c| c.parse::<usize>().ok())
+                .unwrap_or(80);
+            let render = self.menu_renderer.unwrap_or(Self::render_select_menu);
+            let shown: &[String] = if show_menu { &items } else { &[] };
+            let rendered = render(shown, &prompt, columns);
+            let _ = context.stderr.write_all(rendered.as_bytes());
+            let _ = context.stderr.flush();
+
+            // Read one line byte-by-byte so no input beyond the newline is consumed
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            let mut eof = true;
+            while let Ok(1) = context.stdin.read(&mut byte) {
+                eof = false;
+                if byte[0] == b'\n' {
+                    break;
                 }
+                line.push(byte[0]);
             }
-
-            // Copy environment variables
-            for (key, value) in parent_env.iter() {
-                isolated_env.insert(key.clone(), value.clone());
+            if eof && line.is_empty() {
+                let _ = context.stderr.write_all(b"\n");
+                break;
             }
-        }
-
-        // Copy shell options (but reset control flow state)
-        {
-            let parent_options = parent_ctx.options.read().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to read parent options",
-                )
-            })?;
-            let mut isolated_options = isolated_ctx.options.write().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to acquire isolated options lock",
-                )
-            })?;
-
-            *isolated_options = parent_options.clone();
-            // Reset control flow state in subshell
-            isolated_options.break_requested = false;
-            isolated_options.continue_requested = false;
-        }
-
-        // Copy current working directory
-        isolated_ctx.cwd = parent_ctx.cwd.clone();
-
-        // Copy shell level (will be incremented by caller)
-        isolated_ctx.shell_level = parent_ctx.shell_level;
-
-        // Functions and aliases are NOT inherited (subshell isolation)
-        // History is NOT shared (subshell isolation)
 
-        Ok(isolated_ctx)
-    }
-
-    /// Convert AST commands to shell script text for external execution
-    /// This method ensures proper escaping to prevent command injection attacks
-    fn commands_to_script(&self, commands: &[AstNode]) -> ShellResult<String> {
-        let mut script = String::new();
-
-        // Add security headers with strict bash settings
-        script.push_str("#!/bin/bash\n");
-        script.push_str("set -euo pipefail\n");
-        script.push_str("# Generated by NexusShell - DO NOT EDIT MANUALLY\n");
-        script.push_str("export IFS=$' \\t\\n'\n\n"); // Reset IFS to default
-
-        for command in commands {
-            // Convert AST node to shell command text with safety checks
-            script.push_str(&Self::ast_to_command_string_safe(command)?);
-            script.push('\n');
-        }
+            let reply = String::from_utf8_lossy(&line)
+                .trim_end_matches('\r')
+                .to_string();
+            context.set_var("REPLY", reply.clone());
+            // An empty line redisplays the menu without running the body
+            if reply.trim().is_empty() {
+                show_menu = true;
+                continue;
+            }
+            show_menu = false;
 
-        Ok(script)
-    }
+            let choice = reply
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| items.get(i))
+                .cloned()
+                .unwrap_or_default();
+            context.set_var(variable.to_string(), choice);
 
-    /// Convert single AST node to safely escaped command string
-    /// This method prevents command injection by properly escaping all inputs
-    fn ast_to_command_string_safe(node: &AstNode) -> ShellResult<String> {
-        match node {
-            AstNode::Program(statements) => {
-                let mut commands = Vec::new();
-                for statement in statements {
-                    commands.push(Self::ast_to_command_string_safe(statement)?);
-                }
-                Ok(commands.join("; "))
+            let body_result = self.execute_ast_direct(body, context)?;
+            if context.is_interactive() {
+                // Interactive menus must show body output before the next prompt
+                let _ = context.stdout.write_all(body_result.stdout.as_bytes());
+                let _ = context.stdout.flush();
+            } else {
+                stdout.push_str(&body_result.stdout);
             }
-            AstNode::Command { name, args, .. } => {
-                // Safely extract and validate command name
-                let cmd_name = match name.as_ref() {
-                    AstNode::Word(w) => Self::shell_escape_safe(w),
-                    _ => {
-                        return Err(ShellError::new(
-                            ErrorKind::SecurityError(crate::error::SecurityErrorKind::InvalidInput),
-                            "Invalid command name structure - potential security risk",
-                        ))
-                    }
-                };
-
-                // Validate command name to prevent injection
-                if !Self::is_safe_command_name(&cmd_name) {
-                    return Err(ShellError::new(
-                        ErrorKind::SecurityError(crate::error::SecurityErrorKind::InvalidInput),
-                        format!("Unsafe command name detected: {cmd_name}"),
-                    ));
-                }
+            stderr.push_str(&body_result.stderr);
+            last_result = body_result;
 
-                let mut cmd_str = cmd_name;
-                for arg in args {
-                    cmd_str.push(' ');
-                    match arg {
-                        AstNode::Word(word) => {
-                            cmd_str.push_str(&Self::shell_escape_safe(word));
-                        }
-                        AstNode::StringLiteral { value, .. } => {
-                            cmd_str.push_str(&Self::shell_escape_safe(value));
-                        }
-                        AstNode::NumberLiteral { value, .. } => {
-                            cmd_str.push_str(&Self::shell_escape_safe(value));
-                        }
-                        _ => {
-                            return Err(ShellError::new(
-                                ErrorKind::SecurityError(crate::error::SecurityErrorKind::InvalidInput),
-                                "Complex arguments not supported in subshell execution for security reasons"
-                            ));
-                        }
-                    }
-                }
-                Ok(cmd_str)
+            if self.unwinding() {
+                break;
             }
-            AstNode::Word(word) => Ok(Self::shell_escape_safe(word)),
-            AstNode::StringLiteral { value, .. } => Ok(Self::shell_escape_safe(value)),
-            AstNode::NumberLiteral { value, .. } => Ok(Self::shell_escape_safe(value)),
-            AstNode::Pipeline { elements, .. } => {
-                let parts: ShellResult<Vec<String>> = elements
-                    .iter()
-                    .map(|e| Self::ast_to_command_string_safe(e))
-                    .collect();
-                Ok(parts?.join(" | "))
+            if context.should_break() {
+                context.clear_break();
+                break;
             }
-            _ => {
-                // Reject complex nodes for security
-                Err(ShellError::new(
-                    ErrorKind::SecurityError(crate::error::SecurityErrorKind::InvalidInput),
-                    "Complex command structures not supported in subshell for security reasons",
-                ))
+            if context.should_continue() {
+                context.clear_continue();
             }
         }
-    }
-
-    /// Safely escape shell arguments to prevent injection attacks
-    /// Uses single quotes to prevent variable expansion and command substitution
-    fn shell_escape_safe(input: &str) -> String {
-        // Check for potentially dangerous characters
-        if input.contains('\0') {
-            // Null bytes are not allowed
-            return "'<INVALID_NULL_BYTE>'".to_string();
-        }
 
-        // If input contains single quotes, we need special handling
-        if input.contains('\'') {
-            // Replace single quotes with '\'' (end quote, escaped quote, start quote)
-            let escaped = input.replace('\'', "'\"'\"'");
-            format!("'{escaped}'")
-        } else {
-            // Simple case: wrap in single quotes
-            format!("'{input}'")
-        }
+        last_result.stdout = stdout;
+        last_result.stderr = stderr;
+        Ok(last_result)
     }
 
-    /// Validate that a command name is safe (no path traversal or injection attempts)
-    fn is_safe_command_name(cmd: &str) -> bool {
-        // Command name must not be empty
-        if cmd.is_empty() {
-            return false;
-        }
-
-        // Remove quotes for validation
-        let unquoted = cmd.trim_start_matches('\'').trim_end_matches('\'');
-
-        // Must not contain dangerous characters
-        let dangerous_chars = [
-            ';', '&', '|', '`', '$', '(', ')', '{', '}', '<', '>', '\n', '\r', '\t',
-        ];
-        if unquoted.chars().any(|c| dangerous_chars.contains(&c)) {
-            return false;
-        }
-
-        // Must not contain path traversal attempts
-        if unquoted.contains("..") || unquoted.starts_with('/') {
-            return false;
+    /// Lay out `select` menu entries column-major within `columns`, like
+    /// bash, followed by `prompt`
+    fn render_select_menu(items: &[String], prompt: &str, columns: usize) -> String {
+        let number_width = items.len().to_string().len();
+        let item_width = items.iter().map(|i| i.chars().count()).max().unwrap_or(0);
+        // "NN) item" plus two spaces of padding between columns
+        let cell_width = number_width + 2 + item_width + 2;
+        let per_row = (columns / cell_width.max(1)).max(1);
+        let rows = items.len().div_ceil(per_row);
+
+        let mut out = String::new();
+        for row in 0..rows {
+            let mut line = String::new();
+            let mut idx = row;
+            while idx < items.len() {
+                let cell = format!("{:>nw$}) {}", idx + 1, items[idx], nw = number_width);
+                idx += rows;
+                if idx < items.len() {
+                    line.push_str(&format!("{cell:<cell_width$}"));
+                } else {
+                    line.push_str(&cell);
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
         }
+        out.push_str(prompt);
+        out
+    }
 
-        // Must not contain environment variable references
-        if unquoted.contains('$') {
-            return false;
+    /// Collect positional parameters `$1..$N` from the context
+    fn positional_params(context: &ShellContext) -> Vec<String> {
+        let mut params = Vec::new();
+        let mut i = 1;
+        while let Some(value) = context.get_var(&i.to_string()) {
+            params.push(value);
+            i += 1;
         }
-
-        // Must be a valid command name (alphanumeric + underscore + hyphen + dot)
-        unquoted
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        params
     }
 
-    /// Prepare environment for subshell process
-    fn prepare_subshell_environment(
-        &self,
-        ctx: &ShellContext,
-    ) -> ShellResult<std::collections::HashMap<String, String>> {
-        let mut env = std::collections::HashMap::new();
-
-        // Copy exported variables
-        {
-            let vars = ctx.vars.read().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to read variables",
-                )
-            })?;
-            let ctx_env = ctx.env.read().map_err(|_| {
-                ShellError::new(
-                    ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
-                    "Failed to read environment",
-                )
-            })?;
+    /// Get executor statistics
+    pub fn stats(&self) -> &ExecutorStats {
+        &self.stats
+    }
 
-            // Add exported shell variables
-            for (key, var) in vars.iter() {
-                if var.exported {
-                    env.insert(key.clone(), var.value.clone());
-                }
-            }
+    /// Reset executor statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = ExecutorStats::default();
+    }
 
-            // Add environment variables
-            for (key, value) in ctx_env.iter() {
-                env.insert(key.clone(), value.clone());
-            }
+    /// Run `( ... )` in a forked copy of the shell state. Variable, directory,
+    /// option and trap changes stay inside; only output and status come back.
+    fn execute_subshell(
+        &mut self,
+        body: &AstNode,
+        ctx: &mut ShellContext,
+    ) -> ShellResult<ExecutionResult> {
+        let mut sub_ctx = ctx.create_subcontext().map_err(|e| {
+            ShellError::new(
+                ErrorKind::InternalError(crate::error::InternalErrorKind::InvalidState),
+                format!("subshell: {e}"),
+            )
+        })?;
+        if let Ok(mut options) = sub_ctx.options.write() {
+            options.subshell_level += 1;
         }
-
-        // Set subshell-specific variables
-        env.insert("SHLVL".to_string(), (ctx.shell_level + 1).to_string());
-        env.insert("NXSH_SUBSHELL".to_string(), "1".to_string());
-
-        Ok(env)
+        // The subshell reads and writes through the parent's streams, so pipes
+        // and substitution capture still see its output
+        std::mem::swap(&mut sub_ctx.stdin, &mut ctx.stdin);
+        std::mem::swap(&mut sub_ctx.stdout, &mut ctx.stdout);
+        std::mem::swap(&mut sub_ctx.stderr, &mut ctx.stderr);
+        // `set -e` or `return` inside the subshell ends the subshell, not the parent
+        let parent_errexit = std::mem::take(&mut self.errexit_pending);
+        let result = self
+            .execute_ast_direct(body, &mut sub_ctx)
+            .and_then(|mut result| {
+                sub_ctx.set_exit_status(result.exit_code);
+                let exit_trap = self.run_exit_trap(&mut sub_ctx)?;
+                Self::merge_trap_output(&mut result, exit_trap);
+                Ok(result)
+            });
+        self.errexit_pending = parent_errexit;
+        self.return_pending = false;
+        std::mem::swap(&mut sub_ctx.stdin, &mut ctx.stdin);
+        std::mem::swap(&mut sub_ctx.stdout, &mut ctx.stdout);
+        std::mem::swap(&mut sub_ctx.stderr, &mut ctx.stderr);
+        result
     }
 }
 
@@ -0,0 +1,312 @@
+//! csh-style history expansion, and history lookup shared with `fc`
+//!
+//! Expansion runs on interactive input before it is parsed:
+//!
+//! - `!!` is the previous command, `!n` history entry `n`, `!-n` the nth
+//!   previous command, `!str` the latest command starting with `str` and
+//!   `!?str?` the latest containing it
+//! - a word designator after `:` picks words of the event: `!!:2`, `!-2:$`,
+//!   `!vim:1-3`, `!!:*`; `!$`, `!^` and `!*` are short for the last, first
+//!   and all arguments of the previous command
+//! - `^old^new^` at the start of a line repeats the previous command with
+//!   the first `old` replaced by `new`
+//!
+//! `!` is left alone inside single quotes, after a backslash, before
+//! whitespace, `=`, `(` or `"`, and in `$!` and `${!name}`.
+
+/// Expand history references in `line`. `Ok(None)` means the line has none
+/// and runs as typed; an error is the message for a reference that cannot
+/// be resolved, such as `!foo: event not found`.
+pub fn expand(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if let Some(rest) = line.strip_prefix('^') {
+        return quick_substitution(rest, history).map(Some);
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut expanded = false;
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if !in_single => {
+                out.push(c);
+                if let Some(&next) = chars.get(i + 1) {
+                    out.push(next);
+                    i += 1;
+                }
+            }
+            '\'' if !in_double => {
+                in_single = !in_single;
+                out.push(c);
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+                out.push(c);
+            }
+            '!' if !in_single && starts_event(&chars, i) => {
+                let (text, used) = expand_event(&chars[i + 1..], history)?;
+                out.push_str(&text);
+                expanded = true;
+                i += used + 1;
+                continue;
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    Ok(expanded.then_some(out))
+}
+
+/// Index into `history` of the event `spec` names: a positive history
+/// number, a negative offset from the end (`-1` is the last command), or
+/// the prefix of the latest command starting with it
+pub fn find_event(spec: &str, history: &[String]) -> Option<usize> {
+    if let Ok(number) = spec.parse::<i64>() {
+        let index = if number < 0 {
+            history.len() as i64 + number
+        } else {
+            number - 1
+        };
+        return usize::try_from(index).ok().filter(|&i| i < history.len());
+    }
+    history.iter().rposition(|entry| entry.starts_with(spec))
+}
+
+/// Split a command line into words the way history designators count
+/// them, keeping quotes inside their words
+pub fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), c) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (q, '\\') if q != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Whether the `!` at `chars[i]` starts a history reference
+fn starts_event(chars: &[char], i: usize) -> bool {
+    let Some(&next) = chars.get(i + 1) else {
+        return false;
+    };
+    if next.is_whitespace() || matches!(next, '=' | '(' | '"') {
+        return false;
+    }
+    // `$!` and `${!name}` are parameter expansions
+    let prev = i.checked_sub(1).map(|p| chars[p]);
+    let prev2 = i.checked_sub(2).map(|p| chars[p]);
+    !(prev == Some('$') || (prev == Some('{') && prev2 == Some('$')))
+}
+
+/// Expand the reference after a `!`, returning its text and how many
+/// characters of `rest` it used
+fn expand_event(rest: &[char], history: &[String]) -> Result<(String, usize), String> {
+    let previous = || {
+        history
+            .last()
+            .ok_or_else(|| "!!: event not found".to_string())
+    };
+    let (entry, mut used, implied) = match rest[0] {
+        '!' => (previous()?, 1, false),
+        // `!$`, `!^` and `!*` are word designators on the previous command
+        '$' | '^' | '*' => (previous()?, 0, true),
+        '?' => {
+            let end = rest[1..].iter().position(|&c| c == '?');
+            let needle: String = rest[1..end.map_or(rest.len(), |e| e + 1)].iter().collect();
+            let used = end.map_or(rest.len(), |e| e + 2);
+            let entry = history
+                .iter()
+                .rev()
+                .find(|entry| !needle.is_empty() && entry.contains(&needle))
+                .ok_or_else(|| format!("!?{needle}: event not found"))?;
+            (entry, used, false)
+        }
+        _ => {
+            let len = if rest[0] == '-' || rest[0].is_ascii_digit() {
+                1 + rest[1..].iter().take_while(|c| c.is_ascii_digit()).count()
+            } else {
+                rest.iter()
+                    .take_while(|&&c| {
+                        !c.is_whitespace() && !matches!(c, ':' | '"' | '\'' | ';' | '|' | '&')
+                    })
+                    .count()
+            };
+            let spec: String = rest[..len].iter().collect();
+            let entry = find_event(&spec, history)
+                .map(|index| &history[index])
+                .ok_or_else(|| format!("!{spec}: event not found"))?;
+            (entry, len, false)
+        }
+    };
+
+    let designator = if implied {
+        used += 1;
+        Some(rest[0].to_string())
+    } else if rest.get(used) == Some(&':')
+        && rest
+            .get(used + 1)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '^' | '$' | '*' | '-'))
+    {
+        let len = rest[used + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit() || matches!(c, '^' | '$' | '*' | '-'))
+            .count();
+        let spec: String = rest[used + 1..used + 1 + len].iter().collect();
+        used += 1 + len;
+        Some(spec)
+    } else {
+        None
+    };
+
+    match designator {
+        None => Ok((entry.clone(), used)),
+        Some(spec) => {
+            let words = split_words(entry);
+            select_words(&words, &spec)
+                .map(|selected| (selected, used))
+                .ok_or_else(|| format!(":{spec}: bad word specifier"))
+        }
+    }
+}
+
+/// Apply a word designator (`n`, `^`, `$`, `*`, `n-m`, `n-`, `n*`, `-m`)
+fn select_words(words: &[String], spec: &str) -> Option<String> {
+    let last = words.len().checked_sub(1)?;
+    let position = |s: &str| match s {
+        "^" => Some(1),
+        "$" => Some(last),
+        _ => s.parse::<usize>().ok(),
+    };
+    let (from, to) = if spec == "*" {
+        if last == 0 {
+            return Some(String::new());
+        }
+        (1, last)
+    } else if let Some(start) = spec.strip_suffix('*') {
+        (position(start)?, last)
+    } else if let Some((start, end)) = spec.split_once('-') {
+        let from = if start.is_empty() {
+            0
+        } else {
+            position(start)?
+        };
+        // `n-` runs to the word before the last
+        let to = if end.is_empty() {
+            last.checked_sub(1)?
+        } else {
+            position(end)?
+        };
+        (from, to)
+    } else {
+        let n = position(spec)?;
+        (n, n)
+    };
+    (from <= to && to <= last).then(|| words[from..=to].join(" "))
+}
+
+/// `^old^new^rest`: the previous command with the first `old` replaced
+fn quick_substitution(spec: &str, history: &[String]) -> Result<String, String> {
+    let mut parts = spec.splitn(3, '^');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+    let tail = parts.next().unwrap_or_default();
+    let previous = history
+        .last()
+        .ok_or_else(|| "!!: event not found".to_string())?;
+    if old.is_empty() || !previous.contains(old) {
+        return Err(format!("^{old}^{new}: substitution failed"));
+    }
+    Ok(format!("{}{tail}", previous.replacen(old, new, 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        [
+            "ls -l /tmp",
+            "git commit -m 'first try'",
+            "echo one two three",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    fn expanded(line: &str) -> String {
+        expand(line, &history()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn events_and_word_designators() {
+        assert_eq!(expanded("!!"), "echo one two three");
+        assert_eq!(expanded("sudo !-3"), "sudo ls -l /tmp");
+        assert_eq!(expanded("!1 /var"), "ls -l /tmp /var");
+        assert_eq!(expanded("!git"), "git commit -m 'first try'");
+        assert_eq!(expanded("!?commit?"), "git commit -m 'first try'");
+        assert_eq!(expanded("cat !$"), "cat three");
+        assert_eq!(expanded("cat !^ !*"), "cat one one two three");
+        assert_eq!(expanded("!git:3"), "'first try'");
+        assert_eq!(expanded("!!:1-2"), "one two");
+        assert_eq!(expanded("^two^2^!"), "echo one 2 three!");
+    }
+
+    #[test]
+    fn quoting_and_parameters_suppress_expansion() {
+        for line in [
+            "echo '!!'",
+            "echo \\!!",
+            "echo $!",
+            "echo ${!name}",
+            "[ a != b ]",
+        ] {
+            assert_eq!(expand(line, &history()), Ok(None), "{line}");
+        }
+        assert_eq!(
+            expand("!nope", &history()),
+            Err("!nope: event not found".into())
+        );
+        assert_eq!(
+            expand("!!:7", &history()),
+            Err(":7: bad word specifier".into())
+        );
+    }
+
+    #[test]
+    fn find_event_accepts_numbers_offsets_and_prefixes() {
+        let history = history();
+        assert_eq!(find_event("1", &history), Some(0));
+        assert_eq!(find_event("-1", &history), Some(2));
+        assert_eq!(find_event("git", &history), Some(1));
+        assert_eq!(find_event("9", &history), None);
+    }
+}
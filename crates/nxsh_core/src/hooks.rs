@@ -0,0 +1,276 @@
+//! Shell event hooks
+//!
+//! A [`HookRegistry`] holds what runs when the shell reaches an event: shell
+//! code added with `hook add`, or callbacks registered by plugins. The
+//! executor fires `pre_command` and `post_command` around each command line
+//! and `cwd_changed` after one that changed directory; the interactive loop
+//! fires `prompt_render` before drawing the prompt and `job_finished` for
+//! each background job it reports as done.
+//!
+//! Hook code sees the event in variables: `HOOK_EVENT` names it and the
+//! rest depend on the event, see [`HookEvent::values`]. Commands run by a
+//! hook fire no hooks themselves, and `$?` is left as the hook found it.
+
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::ExecutionResult;
+use crate::job::JobId;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// An event hooks can be added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HookKind {
+    /// A command line is about to run
+    PreCommand,
+    /// A command line finished
+    PostCommand,
+    /// A command line changed the working directory
+    CwdChanged,
+    /// The prompt is about to be drawn
+    PromptRender,
+    /// A background job finished
+    JobFinished,
+}
+
+impl HookKind {
+    /// Every event, in the order they are listed
+    pub const ALL: [HookKind; 5] = [
+        HookKind::PreCommand,
+        HookKind::PostCommand,
+        HookKind::CwdChanged,
+        HookKind::PromptRender,
+        HookKind::JobFinished,
+    ];
+
+    /// The event named `name`, such as `cwd_changed`
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Name the event is known by
+    pub fn name(self) -> &'static str {
+        match self {
+            HookKind::PreCommand => "pre_command",
+            HookKind::PostCommand => "post_command",
+            HookKind::CwdChanged => "cwd_changed",
+            HookKind::PromptRender => "prompt_render",
+            HookKind::JobFinished => "job_finished",
+        }
+    }
+}
+
+impl std::fmt::Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An event being fired, with what hooks are told about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookEvent {
+    PreCommand {
+        command: String,
+    },
+    PostCommand {
+        command: String,
+        exit_code: i32,
+        duration: Duration,
+    },
+    CwdChanged {
+        old: String,
+        new: String,
+    },
+    PromptRender,
+    JobFinished {
+        job: JobId,
+        exit_code: i32,
+        command: String,
+    },
+}
+
+impl HookEvent {
+    /// The kind of event this is
+    pub fn kind(&self) -> HookKind {
+        match self {
+            HookEvent::PreCommand { .. } => HookKind::PreCommand,
+            HookEvent::PostCommand { .. } => HookKind::PostCommand,
+            HookEvent::CwdChanged { .. } => HookKind::CwdChanged,
+            HookEvent::PromptRender => HookKind::PromptRender,
+            HookEvent::JobFinished { .. } => HookKind::JobFinished,
+        }
+    }
+
+    /// The variables hook code sees besides `HOOK_EVENT`, in the order a
+    /// plugin hook gets their values as arguments
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        match self {
+            HookEvent::PreCommand { command } => vec![("HOOK_COMMAND", command.clone())],
+            HookEvent::PostCommand {
+                command,
+                exit_code,
+                duration,
+            } => vec![
+                ("HOOK_COMMAND", command.clone()),
+                ("HOOK_STATUS", exit_code.to_string()),
+                ("HOOK_DURATION_MS", duration.as_millis().to_string()),
+            ],
+            HookEvent::CwdChanged { old, new } => {
+                vec![("HOOK_OLDPWD", old.clone()), ("HOOK_PWD", new.clone())]
+            }
+            HookEvent::PromptRender => Vec::new(),
+            HookEvent::JobFinished {
+                job,
+                exit_code,
+                command,
+            } => vec![
+                ("HOOK_JOB", job.to_string()),
+                ("HOOK_STATUS", exit_code.to_string()),
+                ("HOOK_COMMAND", command.clone()),
+            ],
+        }
+    }
+}
+
+/// Runs a callback hook for an event
+pub type HookCallback =
+    Arc<dyn Fn(&HookEvent, &mut ShellContext) -> ShellResult<ExecutionResult> + Send + Sync>;
+
+/// What a hook runs
+#[derive(Clone)]
+pub enum HookAction {
+    /// Shell code
+    Script(String),
+    /// A callback registered by `owner`, such as a plugin, under `name`
+    Callback {
+        owner: String,
+        name: String,
+        run: HookCallback,
+    },
+}
+
+impl std::fmt::Debug for HookAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookAction::Script(code) => f.debug_tuple("Script").field(code).finish(),
+            HookAction::Callback { owner, name, .. } => f
+                .debug_struct("Callback")
+                .field("owner", owner)
+                .field("name", name)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Something run when an event fires
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub kind: HookKind,
+    pub action: HookAction,
+}
+
+impl Hook {
+    /// The shell code or the callback name, as `hook remove` matches it
+    pub fn name(&self) -> &str {
+        match &self.action {
+            HookAction::Script(code) => code,
+            HookAction::Callback { name, .. } => name,
+        }
+    }
+}
+
+/// Hooks in the order they were added, shared between a context and the
+/// state it is saved to so they outlive a single evaluation
+#[derive(Clone, Default)]
+pub struct HookRegistry(Arc<RwLock<Vec<Hook>>>);
+
+impl HookRegistry {
+    /// Run `code` when `kind` fires, returning false if it already does
+    pub fn add_script(&self, kind: HookKind, code: &str) -> bool {
+        let Ok(mut hooks) = self.0.write() else {
+            return false;
+        };
+        let present = hooks.iter().any(|hook| {
+            hook.kind == kind && matches!(&hook.action, HookAction::Script(c) if c == code)
+        });
+        if !present {
+            hooks.push(Hook {
+                kind,
+                action: HookAction::Script(code.to_string()),
+            });
+        }
+        !present
+    }
+
+    /// Call `run` when `kind` fires, in place of a callback `owner` added
+    /// under the same name
+    pub fn add_callback(&self, kind: HookKind, owner: &str, name: &str, run: HookCallback) {
+        if let Ok(mut hooks) = self.0.write() {
+            hooks.retain(|hook| {
+                !(hook.kind == kind
+                    && matches!(&hook.action, HookAction::Callback { owner: o, name: n, .. }
+                        if o == owner && n == name))
+            });
+            hooks.push(Hook {
+                kind,
+                action: HookAction::Callback {
+                    owner: owner.to_string(),
+                    name: name.to_string(),
+                    run,
+                },
+            });
+        }
+    }
+
+    /// Remove the hooks on `kind` whose code or callback name is `name`, or
+    /// every hook on `kind` without a name, returning how many were removed
+    pub fn remove(&self, kind: HookKind, name: Option<&str>) -> usize {
+        let Ok(mut hooks) = self.0.write() else {
+            return 0;
+        };
+        let before = hooks.len();
+        hooks.retain(|hook| hook.kind != kind || name.is_some_and(|name| hook.name() != name));
+        before - hooks.len()
+    }
+
+    /// Remove the callbacks `owner` added, returning how many there were
+    pub fn remove_owner(&self, owner: &str) -> usize {
+        let Ok(mut hooks) = self.0.write() else {
+            return 0;
+        };
+        let before = hooks.len();
+        hooks.retain(
+            |hook| !matches!(&hook.action, HookAction::Callback { owner: o, .. } if o == owner),
+        );
+        before - hooks.len()
+    }
+
+    /// The hooks on `kind`, or all hooks, in the order they run
+    pub fn list(&self, kind: Option<HookKind>) -> Vec<Hook> {
+        self.0
+            .read()
+            .map(|hooks| {
+                hooks
+                    .iter()
+                    .filter(|hook| kind.is_none_or(|kind| hook.kind == kind))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether anything runs when `kind` fires
+    pub fn has(&self, kind: HookKind) -> bool {
+        self.0
+            .read()
+            .map(|hooks| hooks.iter().any(|hook| hook.kind == kind))
+            .unwrap_or(false)
+    }
+}
+
+impl std::fmt::Debug for HookRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.0.read().map(|hooks| hooks.len()).unwrap_or(0);
+        write!(f, "HookRegistry({count})")
+    }
+}
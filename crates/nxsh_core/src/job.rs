@@ -144,6 +144,9 @@ pub struct Job {
     pub pgid: ProcessGroupId,
     /// Whether this job is in the foreground
     pub foreground: bool,
+    /// Whether the processes are an `a &&& b` job group rather than a
+    /// pipeline, which ends with the rightmost non-zero status
+    pub group: bool,
     /// Job creation time
     pub created_at: Instant,
     /// Job completion time
@@ -164,6 +167,7 @@ impl Job {
             status: JobStatus::Running,
             pgid: 0, // Will be set when first process is added
             foreground: false,
+            group: false,
             created_at: Instant::now(),
             completed_at: None,
             working_dir: std::env::current_dir().unwrap_or_default(),
@@ -241,15 +245,17 @@ impl Job {
                     JobStatus::Done(1)
                 }
             } else {
-                // Get exit code from last process (traditional shell behavior)
-                let exit_code = self
-                    .processes
-                    .last()
-                    .and_then(|p| match p.status {
-                        JobStatus::Done(code) => Some(code),
-                        _ => None,
-                    })
-                    .unwrap_or(0);
+                // A pipeline ends with its last process's status (traditional
+                // shell behavior), a job group with its rightmost failure
+                let mut codes = self.processes.iter().rev().map(|p| match p.status {
+                    JobStatus::Done(code) => code,
+                    _ => 0,
+                });
+                let exit_code = if self.group {
+                    codes.find(|&code| code != 0).unwrap_or(0)
+                } else {
+                    codes.next().unwrap_or(0)
+                };
                 JobStatus::Done(exit_code)
             }
         } else {
@@ -317,6 +323,44 @@ impl Job {
         self.foreground = false;
         self.update_status();
     }
+
+    /// Exit status of a finished job as `wait` and `$?` report it
+    pub fn exit_code(&self) -> Option<i32> {
+        match &self.status {
+            JobStatus::Done(code) => Some(*code),
+            JobStatus::Terminated(sig) => Some(128 + sig),
+            JobStatus::Failed(_) => Some(127),
+            _ => None,
+        }
+    }
+
+    /// Status word printed by `jobs` and completion notices
+    pub fn state_label(&self) -> String {
+        match &self.status {
+            JobStatus::Done(0) => "Done".to_string(),
+            JobStatus::Done(code) => format!("Exit {code}"),
+            JobStatus::Terminated(1) => "Hangup".to_string(),
+            JobStatus::Terminated(2) => "Interrupt".to_string(),
+            JobStatus::Terminated(9) => "Killed".to_string(),
+            JobStatus::Terminated(15) => "Terminated".to_string(),
+            JobStatus::Terminated(sig) => format!("Signal {sig}"),
+            JobStatus::Failed(_) => "Exit 127".to_string(),
+            JobStatus::Stopped => "Stopped".to_string(),
+            _ => "Running".to_string(),
+        }
+    }
+
+    /// Line printed by `jobs` and completion notices, e.g.
+    /// `[1]+  Running                 sleep 10 &`
+    pub fn status_line(&self, marker: char) -> String {
+        let suffix = if self.is_running() { " &" } else { "" };
+        format!(
+            "[{}]{marker}  {:<24}{}{suffix}",
+            self.id,
+            self.state_label(),
+            self.description
+        )
+    }
 }
 
 /// Job control signals
@@ -375,6 +419,46 @@ impl JobSignal {
     }
 }
 
+/// Job status for a process that has exited
+#[cfg(not(unix))]
+fn finished_status(exit_status: ExitStatus) -> JobStatus {
+    JobStatus::Done(exit_status.code().unwrap_or(1))
+}
+
+/// Wait for the next change in a job process's state, including stops and
+/// continues, so Ctrl-Z and `bg` show up in the job table
+#[cfg(unix)]
+fn next_process_state(child: &mut std::process::Child) -> (JobStatus, Option<ExitStatus>) {
+    use nxsh_hal::ChildState;
+
+    let status = match nxsh_hal::process::wait_for_state_change(child.id()) {
+        Ok(ChildState::Exited(code)) => JobStatus::Done(code),
+        Ok(ChildState::Signaled(sig)) => JobStatus::Terminated(sig),
+        Ok(ChildState::Stopped(_)) => JobStatus::Stopped,
+        Ok(ChildState::Continued) => JobStatus::Running,
+        Err(e) => JobStatus::Failed(format!("Wait error: {e}")),
+    };
+    (status, None)
+}
+
+/// Wait for a job process to exit
+#[cfg(not(unix))]
+fn next_process_state(child: &mut std::process::Child) -> (JobStatus, Option<ExitStatus>) {
+    match child.wait() {
+        Ok(exit_status) => (finished_status(exit_status), Some(exit_status)),
+        Err(e) => (JobStatus::Failed(format!("Wait error: {e}")), None),
+    }
+}
+
+/// Program and arguments of a prepared command, space separated
+fn command_line(command: &std::process::Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Job manager for handling all jobs in the shell
 pub struct JobManager {
     /// Map of job ID to job
@@ -518,8 +602,10 @@ impl JobManager {
     pub fn create_job(&mut self, description: String) -> ShellResult<JobId> {
         let job_id = {
             let mut next_id = self.get_next_job_id_lock()?;
-            let id = *next_id;
-            *next_id += 1;
+            // Number from one past the highest job still in the table, so
+            // numbers are reused once earlier jobs have been reported
+            let id = self.get_jobs_read()?.keys().max().map_or(1, |max| max + 1);
+            *next_id = id + 1;
             id
         };
 
@@ -721,6 +807,14 @@ impl JobManager {
         })?;
 
         if let Some(job) = jobs.get(&job_id) {
+            // A job whose processes never started has no group; signalling
+            // group 0 would hit the shell itself
+            if job.pgid == 0 {
+                return Err(ShellError::new(
+                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                    format!("Job {job_id} has no running processes"),
+                ));
+            }
             // Send signal to process group
             self.send_signal_to_process_group(job.pgid, signal)
         } else {
@@ -928,7 +1022,7 @@ impl JobManager {
             self.add_process_to_job(job_id, process_info)?;
 
             // Start monitoring thread for this job
-            self.start_job_monitor(job_id, child);
+            self.start_job_monitor(job_id, vec![child]);
         }
 
         #[cfg(windows)]
@@ -1014,7 +1108,7 @@ impl JobManager {
             self.add_process_to_job(job_id, process_info)?;
 
             // Start monitoring thread for this job
-            self.start_job_monitor(job_id, child);
+            self.start_job_monitor(job_id, vec![child]);
         }
 
         // Move job to background
@@ -1023,69 +1117,245 @@ impl JobManager {
         Ok(job_id)
     }
 
-    /// Start a monitoring thread for a background job
-    fn start_job_monitor(&self, job_id: JobId, mut child: std::process::Child) {
-        let jobs = Arc::clone(&self.jobs);
-        let notification_tx = self.notification_tx.clone();
-
-        std::thread::spawn(move || {
-            // Wait for process completion
-            match child.wait() {
-                Ok(exit_status) => {
-                    let new_status = if exit_status.success() {
-                        JobStatus::Done(exit_status.code().unwrap_or(0))
-                    } else {
-                        JobStatus::Failed(format!(
-                            "Process exited with code: {}",
-                            exit_status.code().unwrap_or(-1)
-                        ))
-                    };
+    /// Spawn prepared commands as one background job, each stage's stdout
+    /// feeding the next stage's stdin. All stages join the process group of
+    /// the first; a stage that cannot be started is recorded as failed.
+    pub fn spawn_background_pipeline(
+        &mut self,
+        description: String,
+        stages: Vec<std::process::Command>,
+    ) -> ShellResult<JobId> {
+        self.spawn_background_stages(description, stages, true)
+    }
 
-                    // Update job status
-                    if let Ok(mut jobs_guard) = jobs.write() {
-                        if let Some(job) = jobs_guard.get_mut(&job_id) {
-                            let old_status = job.status.clone();
-                            job.status = new_status.clone();
-                            job.completed_at = Some(std::time::Instant::now());
-
-                            // Update process status
-                            if let Some(process) = job.processes.get_mut(0) {
-                                process.status = new_status.clone();
-                                process.end_time = Some(std::time::Instant::now());
-                                process.exit_status = Some(exit_status);
-                            }
+    /// Spawn the members of an `a &&& b` job group as one background job.
+    /// Like a pipeline's stages they share a process group, but each writes
+    /// to the shell's stdout and the job ends with the rightmost non-zero
+    /// status.
+    pub fn spawn_background_group(
+        &mut self,
+        description: String,
+        members: Vec<std::process::Command>,
+    ) -> ShellResult<JobId> {
+        self.spawn_background_stages(description, members, false)
+    }
 
-                            // Send notification
-                            let _ = notification_tx.send(JobNotification::StatusChanged {
-                                job_id,
-                                old_status,
-                                new_status,
-                            });
-                        }
-                    }
+    fn spawn_background_stages(
+        &mut self,
+        description: String,
+        mut stages: Vec<std::process::Command>,
+        piped: bool,
+    ) -> ShellResult<JobId> {
+        use std::process::Stdio;
+
+        let job_id = self.create_job(description)?;
+        self.with_job_mut(job_id, |job| job.group = !piped);
+        let last = stages.len().saturating_sub(1);
+        let mut children: Vec<std::process::Child> = Vec::with_capacity(stages.len());
+        let mut upstream: Option<std::process::ChildStdout> = None;
+
+        for (index, stage) in stages.iter_mut().enumerate() {
+            match upstream.take() {
+                Some(stdout) => stage.stdin(Stdio::from(stdout)),
+                None => stage.stdin(Stdio::null()),
+            };
+            if piped && index < last {
+                stage.stdout(Stdio::piped());
+            }
+            let leader = children.first().map(|child| child.id());
+            nxsh_hal::process::prepare_job_command(stage, leader.unwrap_or(0));
+
+            let line = command_line(stage);
+            match stage.spawn() {
+                Ok(mut child) => {
+                    upstream = child.stdout.take();
+                    let pid = child.id();
+                    let process = ProcessInfo::new(pid, leader.unwrap_or(pid), line);
+                    self.add_process_to_job(job_id, process)?;
+                    children.push(child);
                 }
                 Err(e) => {
-                    eprintln!("Error waiting for background job {job_id}: {e}");
+                    let program = stage.get_program().to_string_lossy().into_owned();
+                    let mut process = ProcessInfo::new(0, 0, line);
+                    process.update_status(JobStatus::Failed(format!("{program}: {e}")));
+                    self.add_process_to_job(job_id, process)?;
+                }
+            }
+        }
+
+        self.move_job_to_background(job_id)?;
+        self.start_job_monitor(job_id, children);
+        Ok(job_id)
+    }
+
+    /// Record a job that already ran to completion inside the shell
+    pub fn record_finished_job(
+        &mut self,
+        description: String,
+        exit_code: i32,
+    ) -> ShellResult<JobId> {
+        let job_id = self.create_job(description)?;
+        self.with_job_mut(job_id, |job| {
+            job.status = JobStatus::Done(exit_code);
+            job.completed_at = Some(Instant::now());
+        });
+        Ok(job_id)
+    }
+
+    /// Add a foreground job that the user stopped with Ctrl-Z. The job keeps
+    /// its process group and stays stopped until `fg` or `bg` resumes it.
+    pub fn adopt_stopped_job(
+        &mut self,
+        description: String,
+        children: Vec<std::process::Child>,
+        pgid: ProcessGroupId,
+    ) -> ShellResult<JobId> {
+        let job_id = self.create_job(description.clone())?;
+        for child in &children {
+            let mut process = ProcessInfo::new(child.id(), pgid, description.clone());
+            process.update_status(JobStatus::Stopped);
+            self.add_process_to_job(job_id, process)?;
+        }
+        self.with_job_mut(job_id, |job| {
+            job.foreground = false;
+            job.update_status();
+        });
+        self.start_job_monitor(job_id, children);
+        Ok(job_id)
+    }
 
-                    // Update job as failed
-                    if let Ok(mut jobs_guard) = jobs.write() {
-                        if let Some(job) = jobs_guard.get_mut(&job_id) {
-                            let old_status = job.status.clone();
-                            let new_status = JobStatus::Failed(format!("Wait error: {e}"));
-                            job.status = new_status.clone();
-                            job.completed_at = Some(std::time::Instant::now());
+    /// Start a monitoring thread per process of a job, following it through
+    /// stops and continues until it exits
+    fn start_job_monitor(&self, job_id: JobId, children: Vec<std::process::Child>) {
+        for mut child in children {
+            let jobs = Arc::clone(&self.jobs);
+            let notification_tx = self.notification_tx.clone();
+
+            std::thread::spawn(move || loop {
+                let pid = child.id();
+                let (new_status, exit_status) = next_process_state(&mut child);
+                let finished = !matches!(new_status, JobStatus::Stopped | JobStatus::Running);
+
+                // Update process and job status
+                if let Ok(mut jobs_guard) = jobs.write() {
+                    if let Some(job) = jobs_guard.get_mut(&job_id) {
+                        let old_status = job.status.clone();
+                        if let Some(process) = job.get_process_mut(pid) {
+                            process.update_status(new_status);
+                            process.exit_status = exit_status;
+                        }
+                        job.update_status();
 
+                        if job.status != old_status {
                             // Send notification
                             let _ = notification_tx.send(JobNotification::StatusChanged {
                                 job_id,
                                 old_status,
-                                new_status,
+                                new_status: job.status.clone(),
                             });
                         }
                     }
                 }
+
+                if finished {
+                    break;
+                }
+            });
+        }
+    }
+
+    /// The current (`%+`) and previous (`%-`) jobs: the two most recently
+    /// created ones still in the table
+    pub fn current_and_previous(&self) -> (Option<JobId>, Option<JobId>) {
+        let mut ids: Vec<JobId> = self
+            .jobs
+            .read()
+            .map(|jobs| jobs.keys().copied().collect())
+            .unwrap_or_default();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        (ids.first().copied(), ids.get(1).copied())
+    }
+
+    /// Marker printed after a job number: `+` current, `-` previous
+    pub fn job_marker(&self, job_id: JobId) -> char {
+        match self.current_and_previous() {
+            (Some(current), _) if current == job_id => '+',
+            (_, Some(previous)) if previous == job_id => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Resolve a job spec: `%n`, `%%`/`%+`/`%` (current), `%-` (previous),
+    /// `%name` (command prefix) or `%?text` (command substring)
+    pub fn resolve_job_spec(&self, spec: &str) -> ShellResult<JobId> {
+        let invalid = |message: String| {
+            ShellError::new(
+                ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                message,
+            )
+        };
+        let key = spec.strip_prefix('%').unwrap_or(spec);
+        let (current, previous) = self.current_and_previous();
+        let found = match key {
+            "" | "%" | "+" => current,
+            "-" => previous.or(current),
+            _ if key.bytes().all(|b| b.is_ascii_digit()) => key
+                .parse::<JobId>()
+                .ok()
+                .filter(|id| self.get_job(*id).ok().flatten().is_some()),
+            _ => {
+                let matching: Vec<JobId> = self
+                    .get_all_jobs()
+                    .into_iter()
+                    .filter(|job| match key.strip_prefix('?') {
+                        Some(text) => job.description.contains(text),
+                        None => job.description.starts_with(key),
+                    })
+                    .map(|job| job.id)
+                    .collect();
+                if matching.len() > 1 {
+                    return Err(invalid(format!("{spec}: ambiguous job spec")));
+                }
+                matching.first().copied()
             }
-        });
+        };
+        found.ok_or_else(|| invalid(format!("{spec}: no such job")))
+    }
+
+    /// Job that owns the process `pid`
+    pub fn job_for_pid(&self, pid: ProcessId) -> Option<JobId> {
+        let jobs = self.jobs.read().ok()?;
+        jobs.values()
+            .find(|job| job.get_process(pid).is_some())
+            .map(|job| job.id)
+    }
+
+    /// Status lines for finished jobs, removing them from the table as they
+    /// are reported (`[1]+  Done                    sleep 1`)
+    pub fn take_completion_notices(&mut self) -> Vec<String> {
+        self.take_finished_jobs()
+            .into_iter()
+            .map(|(_, notice)| notice)
+            .collect()
+    }
+
+    /// Finished jobs in ID order, each with its status line, removing them
+    /// from the table as [`Self::take_completion_notices`] does
+    pub fn take_finished_jobs(&mut self) -> Vec<(Job, String)> {
+        let mut finished: Vec<Job> = self
+            .get_all_jobs()
+            .into_iter()
+            .filter(Job::is_finished)
+            .collect();
+        finished.sort_by_key(|job| job.id);
+        let notices: Vec<String> = finished
+            .iter()
+            .map(|job| job.status_line(self.job_marker(job.id)))
+            .collect();
+        for job in &finished {
+            self.remove_job(job.id);
+        }
+        finished.into_iter().zip(notices).collect()
     }
 
     /// Get job notifications channel receiver
@@ -1122,6 +1392,7 @@ impl JobManager {
         }
 
         // Update job status
+        let stopped;
         {
             let mut jobs = self.jobs.write().map_err(|_| {
                 ShellError::new(
@@ -1132,11 +1403,7 @@ impl JobManager {
 
             if let Some(job) = jobs.get_mut(&job_id) {
                 job.move_to_foreground();
-
-                // Continue the job if it was stopped
-                if job.is_stopped() {
-                    self.send_signal_to_job(job_id, JobSignal::Continue)?;
-                }
+                stopped = job.is_stopped();
             } else {
                 return Err(ShellError::new(
                     ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
@@ -1145,6 +1412,11 @@ impl JobManager {
             }
         }
 
+        // Continue the job if it was stopped
+        if stopped {
+            self.resume_job(job_id)?;
+        }
+
         Ok(())
     }
 
@@ -1164,6 +1436,7 @@ impl JobManager {
         }
 
         // Update job status
+        let stopped;
         {
             let mut jobs = self.jobs.write().map_err(|_| {
                 ShellError::new(
@@ -1174,11 +1447,7 @@ impl JobManager {
 
             if let Some(job) = jobs.get_mut(&job_id) {
                 job.move_to_background();
-
-                // Continue the job if it was stopped
-                if job.is_stopped() {
-                    self.send_signal_to_job(job_id, JobSignal::Continue)?;
-                }
+                stopped = job.is_stopped();
             } else {
                 return Err(ShellError::new(
                     ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
@@ -1187,6 +1456,26 @@ impl JobManager {
             }
         }
 
+        // Continue the job if it was stopped
+        if stopped {
+            self.resume_job(job_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Send SIGCONT to a stopped job and mark its stopped processes running
+    /// right away, so a caller waiting on it does not see the stale state
+    fn resume_job(&self, job_id: JobId) -> ShellResult<()> {
+        self.send_signal_to_job(job_id, JobSignal::Continue)?;
+        self.with_job_mut(job_id, |job| {
+            for process in &mut job.processes {
+                if process.status == JobStatus::Stopped {
+                    process.update_status(JobStatus::Running);
+                }
+            }
+            job.update_status();
+        });
         Ok(())
     }
 
@@ -1224,6 +1513,23 @@ impl JobManager {
         }
     }
 
+    /// Wait for a foreground job to finish or be stopped again
+    pub fn wait_for_foreground_job(&self, job_id: JobId) -> ShellResult<JobStatus> {
+        loop {
+            match self.get_job(job_id)? {
+                Some(job) if job.is_finished() || job.is_stopped() => return Ok(job.status),
+                Some(_) => {}
+                None => {
+                    return Err(ShellError::new(
+                        ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                        format!("Job {job_id} not found"),
+                    ))
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
     /// Clean up finished jobs
     pub fn cleanup_finished_jobs(&mut self) -> ShellResult<()> {
         let finished_jobs: Vec<JobId> = {
@@ -7,7 +7,11 @@
 // Re-export commonly used types and functions
 pub use context::{Context, ShellContext};
 pub use error::{ErrorKind, ShellError, ShellResult};
-pub use executor::{Builtin, ExecutionResult, Executor};
+pub use executor::{
+    Builtin, CommandReport, ExecutionResult, Executor, MenuRenderer, PostCommandHook,
+    PostCommandHooks, StructuredBuiltin, TableRenderer,
+};
+pub use hooks::{Hook, HookAction, HookEvent, HookKind, HookRegistry};
 pub use job::{Job, JobManager, JobStatus};
 #[cfg(feature = "logging")]
 pub use logging::LoggingSystem;
@@ -15,8 +19,11 @@ pub use logging::LoggingSystem;
 pub use metrics::{MetricsConfig, MetricsSystem};
 pub use namespace::{ImportStatement, Module, NamespaceSystem, Symbol};
 pub use pattern_matching::{MatchResult, PatternMatchingEngine, PatternValue};
+pub use plugin_commands::{
+    CompletionSpec, PluginCommand, PluginCommandRegistry, PluginCompleter, PromptSegment,
+};
 pub use shell::{Config, Shell, ShellState};
-pub use stream::{Stream, StreamData, StreamType};
+pub use stream::{OutputTarget, PipeWriter, Stream, StreamData, StreamType};
 // Removed safe crate imports - implementing custom safe wrappers instead
 #[cfg(feature = "advanced_scheduler")]
 pub use advanced_scheduler::{
@@ -65,6 +72,7 @@ pub use test_framework::{ComprehensiveTestReport, PerformanceBenchmark, TestFram
 // Public modules
 #[cfg(feature = "advanced_scheduler")]
 pub mod advanced_scheduler;
+pub mod arithmetic; // Shell integer arithmetic ((...)) evaluation
 pub mod builtins;
 pub mod closures; // First-class function and closure support
 pub mod compat; // new compatibility layer (anyhow substitute)
@@ -76,6 +84,8 @@ pub mod encryption;
 pub mod error;
 pub mod error_handling; // Advanced error handling system
 pub mod executor;
+pub mod history; // csh-style history expansion and fc event lookup
+pub mod hooks; // Shell event hooks run by the executor and the prompt
 #[cfg(feature = "internationalization")]
 pub mod i18n;
 #[cfg(feature = "heavy-time")]
@@ -101,6 +111,7 @@ pub mod pattern_matching; // Advanced pattern matching engine
 pub mod performance; // Performance optimization system
 #[cfg(feature = "performance_profiler")]
 pub mod performance_profiler; // Performance profiling and benchmarking - Phase 4
+pub mod plugin_commands; // Commands registered by loaded plugins
 #[cfg(feature = "powershell_compat")]
 pub mod powershell_compat;
 pub mod result;
@@ -116,8 +127,10 @@ pub mod structured_data; // NexusShell-inspired structured data processing
 pub mod structured_logging;
 #[cfg(feature = "system_optimizer")]
 pub mod system_optimizer; // Advanced system optimization and tuning - Phase 4
+pub mod test_expr; // [[ ... ]] conditional expression evaluation
 #[cfg(feature = "test_framework")]
 pub mod test_framework; // Comprehensive testing framework - Phase 4
+pub mod trap; // trap registry for signals and EXIT/ERR/DEBUG
 pub mod updater; // PowerShell compatibility mode
 
 // Re-export after module declarations to avoid unresolved import during compilation order
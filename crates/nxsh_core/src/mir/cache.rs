@@ -0,0 +1,208 @@
+//! On-disk cache of compiled scripts.
+//!
+//! A script is compiled once to an optimized `MirProgram` and stored under a
+//! key hashed from its source and the compiler version, so later runs of the
+//! same script skip parsing and lowering while an edited script or a newer
+//! nxsh simply misses the cache. Entries are written atomically, a damaged
+//! entry is dropped on load, and after every store the entries unused for
+//! longer than the maximum age go first, then the least recently used ones
+//! until the cache fits its size limit.
+
+use super::{lower::Lowerer, optimizer, MirProgram};
+use bincode::Options;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Bumped whenever lowering or the encoding changes what a stored program means
+const FORMAT_VERSION: u32 = 3;
+
+/// Start of every entry, followed by `FORMAT_VERSION` in little endian
+const MAGIC: &[u8; 6] = b"NXMIR\0";
+
+const ENTRY_EXTENSION: &str = "mir";
+
+/// Default size limit of the cache directory
+pub const DEFAULT_MAX_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Default time an entry is kept without being used
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Lower and optimize a parsed script the way the MIR engine runs it
+pub fn compile(ast: &nxsh_parser::ast::AstNode) -> MirProgram {
+    let mut program = Lowerer::new().lower_program(ast);
+    optimizer::optimize_program(&mut program);
+    program
+}
+
+/// Directory of cached MIR programs
+#[derive(Debug, Clone)]
+pub struct MirCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_age: Duration,
+}
+
+impl MirCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// The cache the environment configures, or None when `NXSH_MIR_CACHE=0`
+    /// turns it off. `NXSH_MIR_CACHE_DIR` overrides the directory, which is
+    /// `$XDG_CACHE_HOME/nxsh/mir` or `~/.cache/nxsh/mir` (`%LOCALAPPDATA%\nxsh\mir`
+    /// on Windows), and `NXSH_MIR_CACHE_SIZE` the size limit in bytes.
+    pub fn from_env() -> Option<Self> {
+        if std::env::var_os("NXSH_MIR_CACHE").is_some_and(|v| v == "0") {
+            return None;
+        }
+        let dir = match std::env::var_os("NXSH_MIR_CACHE_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => default_dir()?,
+        };
+        let mut cache = Self::new(dir);
+        if let Some(bytes) = std::env::var("NXSH_MIR_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+        {
+            cache.max_bytes = bytes;
+        }
+        Some(cache)
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File the program compiled from `source` is stored in
+    pub fn entry_path(&self, source: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(FORMAT_VERSION.to_le_bytes());
+        hasher.update(source);
+        self.dir
+            .join(hex::encode(hasher.finalize()))
+            .with_extension(ENTRY_EXTENSION)
+    }
+
+    /// The program stored for `source`, marking the entry as used. An entry
+    /// that cannot be decoded is removed.
+    pub fn load(&self, source: &str) -> Option<MirProgram> {
+        let path = self.entry_path(source);
+        let bytes = fs::read(&path).ok()?;
+        match decode(&bytes) {
+            Some(program) => {
+                // Eviction goes by modification time
+                let _ = fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                Some(program)
+            }
+            None => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Store the program compiled from `source`, then evict old entries.
+    /// Returns the entry's path.
+    pub fn store(&self, source: &str, program: &MirProgram) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        encoding()
+            .serialize_into(&mut bytes, program)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // Write beside the entry and rename, so a concurrent run never
+        // reads a partly written program
+        let path = self.entry_path(source);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&partial, &bytes)?;
+        if let Err(e) = fs::rename(&partial, &path) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        self.evict()?;
+        Ok(path)
+    }
+
+    /// Remove entries unused for longer than the maximum age, then the least
+    /// recently used ones until the cache fits its size limit. Returns the
+    /// number of entries removed.
+    pub fn evict(&self) -> io::Result<usize> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == ENTRY_EXTENSION) {
+                let meta = entry.metadata()?;
+                let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((used, meta.len(), path));
+            }
+        }
+        entries.sort();
+
+        let now = SystemTime::now();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        let mut removed = 0;
+        for (used, len, path) in entries {
+            let expired = now.duration_since(used).is_ok_and(|age| age > self.max_age);
+            if !expired && total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                // Another run evicted it first
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            total -= len;
+        }
+        Ok(removed)
+    }
+}
+
+fn encoding() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+fn decode(bytes: &[u8]) -> Option<MirProgram> {
+    let payload = bytes.strip_prefix(MAGIC.as_slice())?;
+    let (version, payload) = payload.split_first_chunk::<4>()?;
+    if u32::from_le_bytes(*version) != FORMAT_VERSION {
+        return None;
+    }
+    // A damaged length prefix must not make the decoder allocate more than the file holds
+    encoding()
+        .with_limit(payload.len() as u64)
+        .deserialize(payload)
+        .ok()
+}
+
+fn default_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ if cfg!(windows) => PathBuf::from(std::env::var_os("LOCALAPPDATA")?),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("nxsh").join("mir"))
+}
@@ -0,0 +1,380 @@
+//! Commands run by MIR programs.
+//!
+//! A command a program does not define as a function is handed to a
+//! `CommandHost`. Inside a shell that is `ShellCommands`, which looks the
+//! name up the way the executor does for a simple command: registered
+//! builtins (including those of `nxsh_builtins`), then plugin commands, then
+//! a program found through the shell's command hash. Builtins read piped
+//! input from the context's stdin and return their output; external programs
+//! get the input on a pipe and inherit the terminal unless their output is
+//! captured. A pipeline starts its external stages with `spawn` so they run
+//! at the same time, connected by pipes.
+
+use super::MirError;
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::{Builtin, ExecutionResult, Executor};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Child, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Runs the commands of a MIR program
+pub trait CommandHost {
+    /// Run `name` with `args`. With `stdin` the command reads that input
+    /// instead of the shell's; with `capture` its standard output must be
+    /// returned, otherwise it may go straight to the terminal.
+    fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        capture: bool,
+    ) -> Result<CommandOutput, MirError>;
+
+    /// Start `name` running alongside the caller, writing its standard
+    /// output to `stdout`, or without one returning it when `capture` is set.
+    /// `None` means the command runs in-process and must go through `run`.
+    fn spawn(
+        &mut self,
+        _name: &str,
+        _args: &[String],
+        _stdin: Option<&PipeInput>,
+        _stdout: Option<File>,
+        _capture: bool,
+    ) -> Option<RunningCommand> {
+        None
+    }
+
+    /// Whether the shell option `name` (`set -o name`) is on
+    fn option(&self, _name: &str) -> bool {
+        false
+    }
+}
+
+/// What a command printed and its exit status
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl From<ExecutionResult> for CommandOutput {
+    fn from(result: ExecutionResult) -> Self {
+        Self {
+            status: result.exit_code,
+            stdout: result.stdout,
+            stderr: result.stderr,
+        }
+    }
+}
+
+/// Input piped into a command: output collected from the previous stage,
+/// or the read end of a pipe another process is still writing. Clones share
+/// one read position, so the commands of a compound pipeline stage read on
+/// where the previous one stopped, as they would from a real pipe.
+#[derive(Debug, Clone)]
+pub struct PipeInput(Arc<Mutex<Source>>);
+
+#[derive(Debug)]
+enum Source {
+    Buffer(io::Cursor<Vec<u8>>),
+    Stream(File),
+}
+
+impl PipeInput {
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        Self(Arc::new(Mutex::new(Source::Buffer(io::Cursor::new(
+            data.into(),
+        )))))
+    }
+
+    /// Input read from `pipe` as its writer produces it
+    pub fn stream(pipe: File) -> Self {
+        Self(Arc::new(Mutex::new(Source::Stream(pipe))))
+    }
+
+    /// Another handle on the pipe of a streamed input, for a process to
+    /// read directly
+    pub fn pipe(&self) -> Option<File> {
+        match &*self.0.lock().ok()? {
+            Source::Stream(pipe) => pipe.try_clone().ok(),
+            Source::Buffer(_) => None,
+        }
+    }
+
+    /// Everything not read yet, which counts as read afterwards
+    pub fn take_rest(&self) -> Vec<u8> {
+        let mut rest = Vec::new();
+        let _ = self.clone().read_to_end(&mut rest);
+        rest
+    }
+}
+
+impl Read for PipeInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.lock().as_deref_mut() {
+            Ok(Source::Buffer(cursor)) => cursor.read(buf),
+            Ok(Source::Stream(pipe)) => pipe.read(buf),
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+impl Default for PipeInput {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+/// A command started by `CommandHost::spawn`
+#[derive(Debug)]
+pub struct RunningCommand(Running);
+
+#[derive(Debug)]
+enum Running {
+    Process {
+        name: String,
+        child: Child,
+        feeder: Option<JoinHandle<()>>,
+    },
+    /// The command could not be started
+    Finished(CommandOutput),
+}
+
+impl RunningCommand {
+    /// Wait for the command to exit. The output holds its standard output
+    /// if that was captured
+    pub fn wait(self) -> Result<CommandOutput, MirError> {
+        let (name, child, feeder) = match self.0 {
+            Running::Process {
+                name,
+                child,
+                feeder,
+            } => (name, child, feeder),
+            Running::Finished(output) => return Ok(output),
+        };
+        let output = child
+            .wait_with_output()
+            .map_err(|e| MirError::Runtime(format!("{name}: {e}")))?;
+        if let Some(feeder) = feeder {
+            let _ = feeder.join();
+        }
+        let (status, signal_message) = nxsh_hal::command::describe_exit(output.status);
+        Ok(CommandOutput {
+            status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: signal_message
+                .map(|message| format!("{message}\n"))
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Runs commands with a shell's builtins and context
+pub struct ShellCommands<'a> {
+    builtins: &'a HashMap<String, Arc<dyn Builtin>>,
+    context: &'a mut ShellContext,
+}
+
+impl<'a> ShellCommands<'a> {
+    pub fn new(
+        builtins: &'a HashMap<String, Arc<dyn Builtin>>,
+        context: &'a mut ShellContext,
+    ) -> Self {
+        Self { builtins, context }
+    }
+
+    /// Run an in-process command with `stdin` in place of the context's input
+    fn run_in_process(
+        &mut self,
+        name: &str,
+        stdin: Option<&PipeInput>,
+        run: impl FnOnce(&mut ShellContext) -> ShellResult<ExecutionResult>,
+    ) -> Result<CommandOutput, MirError> {
+        let saved =
+            stdin.map(|input| std::mem::replace(&mut self.context.stdin, Box::new(input.clone())));
+        let result = run(self.context);
+        if let Some(saved) = saved {
+            self.context.stdin = saved;
+        }
+        result
+            .map(CommandOutput::from)
+            .map_err(|e| MirError::Runtime(format!("{name}: {e}")))
+    }
+
+    /// Start an external program. A streamed input is handed over as the
+    /// program's stdin; other input is written to it from a thread
+    fn start_external(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        stdout: Option<File>,
+        capture: bool,
+    ) -> RunningCommand {
+        let mut command = Executor::external_command(name, args, self.context);
+        let pipe = stdin.and_then(PipeInput::pipe);
+        match (stdin, pipe) {
+            (_, Some(pipe)) => {
+                command.stdin(pipe);
+            }
+            (Some(_), None) => {
+                command.stdin(Stdio::piped());
+            }
+            (None, None) => {}
+        }
+        match stdout {
+            Some(pipe) => {
+                command.stdout(pipe);
+            }
+            None if capture => {
+                command.stdout(Stdio::piped());
+            }
+            None => {}
+        }
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let (status, stderr) = match e.kind() {
+                    io::ErrorKind::NotFound => (127, format!("nxsh: command not found: {name}\n")),
+                    io::ErrorKind::PermissionDenied => {
+                        (126, format!("nxsh: {name}: Permission denied\n"))
+                    }
+                    _ => (126, format!("nxsh: {name}: {e}\n")),
+                };
+                return RunningCommand(Running::Finished(CommandOutput {
+                    status,
+                    stdout: String::new(),
+                    stderr,
+                }));
+            }
+        };
+        // Feed the input from another thread: a command that fills its
+        // output pipe before reading everything would otherwise never finish
+        let feeder = match (stdin, child.stdin.take()) {
+            (Some(input), Some(mut pipe)) => {
+                let data = input.take_rest();
+                Some(std::thread::spawn(move || {
+                    // The command may exit without reading it all
+                    let _ = pipe.write_all(&data);
+                }))
+            }
+            _ => None,
+        };
+        RunningCommand(Running::Process {
+            name: name.to_string(),
+            child,
+            feeder,
+        })
+    }
+
+    /// Whether `name` is a builtin or plugin command, run in-process
+    fn in_process(&self, name: &str) -> bool {
+        self.builtins.contains_key(name) || self.context.plugin_commands.contains(name)
+    }
+}
+
+impl CommandHost for ShellCommands<'_> {
+    fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        capture: bool,
+    ) -> Result<CommandOutput, MirError> {
+        if let Some(builtin) = self.builtins.get(name).cloned() {
+            return self.run_in_process(name, stdin, |context| builtin.execute(context, args));
+        }
+        if let Some(command) = self.context.plugin_commands.get(name) {
+            return self.run_in_process(name, stdin, |context| command.execute(context, args));
+        }
+        self.start_external(name, args, stdin, None, capture).wait()
+    }
+
+    fn spawn(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        stdout: Option<File>,
+        capture: bool,
+    ) -> Option<RunningCommand> {
+        if self.in_process(name) {
+            return None;
+        }
+        Some(self.start_external(name, args, stdin, stdout, capture))
+    }
+
+    fn option(&self, name: &str) -> bool {
+        self.context.get_option(name).unwrap_or(false)
+    }
+}
+
+/// Commands of a `MirExecutor` used outside a shell: the core builtins and
+/// external programs, in a shell context of its own
+pub struct LocalCommands {
+    builtins: HashMap<String, Arc<dyn Builtin>>,
+    context: ShellContext,
+}
+
+impl LocalCommands {
+    pub fn new() -> Self {
+        let builtins = crate::builtins::register_all_builtins()
+            .into_iter()
+            .map(|builtin| (builtin.name().to_string(), builtin))
+            .collect();
+        Self {
+            builtins,
+            context: ShellContext::new(),
+        }
+    }
+}
+
+impl Default for LocalCommands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for LocalCommands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut builtins: Vec<&String> = self.builtins.keys().collect();
+        builtins.sort();
+        f.debug_struct("LocalCommands")
+            .field("builtins", &builtins)
+            .field("cwd", &self.context.cwd)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CommandHost for LocalCommands {
+    fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        capture: bool,
+    ) -> Result<CommandOutput, MirError> {
+        ShellCommands::new(&self.builtins, &mut self.context).run(name, args, stdin, capture)
+    }
+
+    fn spawn(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        stdout: Option<File>,
+        capture: bool,
+    ) -> Option<RunningCommand> {
+        ShellCommands::new(&self.builtins, &mut self.context)
+            .spawn(name, args, stdin, stdout, capture)
+    }
+
+    fn option(&self, name: &str) -> bool {
+        self.context.get_option(name).unwrap_or(false)
+    }
+}
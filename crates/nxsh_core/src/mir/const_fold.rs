@@ -0,0 +1,136 @@
+//! Compile-time evaluation of MIR instructions with constant operands.
+//!
+//! Folding must agree with what the executor computes at run time, so an
+//! instruction is only folded when running it could not fail: division by
+//! zero, overflow and type mismatches are left for the executor to report.
+
+use super::{as_integer, status_of, truthy, MirInstruction, MirValue};
+
+/// Scalar constants that operands may be replaced with. Arrays and objects
+/// are left in registers so they are not copied into every use
+pub(crate) fn is_constant(value: &MirValue) -> bool {
+    matches!(
+        value,
+        MirValue::Integer(_)
+            | MirValue::Float(_)
+            | MirValue::String(_)
+            | MirValue::Boolean(_)
+            | MirValue::Null
+    )
+}
+
+/// Value computed by `inst` if all its operands are constants and running
+/// it cannot fail
+pub(crate) fn fold(inst: &MirInstruction) -> Option<MirValue> {
+    use MirInstruction::*;
+    match inst {
+        Add { left, right, .. }
+        | Sub { left, right, .. }
+        | Mul { left, right, .. }
+        | Div { left, right, .. }
+        | Mod { left, right, .. }
+        | Pow {
+            base: left,
+            exp: right,
+            ..
+        }
+        | BitAnd { left, right, .. }
+        | BitOr { left, right, .. }
+        | BitXor { left, right, .. }
+        | Shl { left, right, .. }
+        | Shr { left, right, .. } => {
+            let (a, b) = (integer(left)?, integer(right)?);
+            let shift = || u32::try_from(b).ok().filter(|&b| b < i64::BITS);
+            let value = match inst {
+                Add { .. } => a.checked_add(b)?,
+                Sub { .. } => a.checked_sub(b)?,
+                Mul { .. } => a.checked_mul(b)?,
+                Div { .. } => a.checked_div(b)?,
+                Mod { .. } => a.checked_rem(b)?,
+                Pow { .. } => a.checked_pow(u32::try_from(b).ok()?)?,
+                BitAnd { .. } => a & b,
+                BitOr { .. } => a | b,
+                BitXor { .. } => a ^ b,
+                Shl { .. } => a << shift()?,
+                _ => a >> shift()?,
+            };
+            Some(MirValue::Integer(value))
+        }
+        Equal { left, right, .. } | NotEqual { left, right, .. } => {
+            let (left, right) = (constant(left)?, constant(right)?);
+            Some(MirValue::Boolean(
+                (left == right) == matches!(inst, Equal { .. }),
+            ))
+        }
+        LessThan { left, right, .. }
+        | LessEqual { left, right, .. }
+        | GreaterThan { left, right, .. }
+        | GreaterEqual { left, right, .. } => {
+            let (a, b) = (integer(left)?, integer(right)?);
+            Some(MirValue::Boolean(match inst {
+                LessThan { .. } => a < b,
+                LessEqual { .. } => a <= b,
+                GreaterThan { .. } => a > b,
+                _ => a >= b,
+            }))
+        }
+        And { left, right, .. } | Or { left, right, .. } => {
+            let (MirValue::Boolean(a), MirValue::Boolean(b)) = (left, right) else {
+                return None;
+            };
+            Some(MirValue::Boolean(match inst {
+                And { .. } => *a && *b,
+                _ => *a || *b,
+            }))
+        }
+        Not { operand, .. } => Some(MirValue::Boolean(!truthy(constant(operand)?))),
+        Status { value, .. } => Some(MirValue::Integer(status_of(constant(value)?))),
+        Concat { parts, .. } => parts
+            .iter()
+            .map(|part| text(constant(part)?))
+            .collect::<Option<String>>()
+            .map(MirValue::string),
+        StringLength { string, .. } => match constant(string)? {
+            MirValue::String(s) => Some(MirValue::Integer(s.chars().count() as i64)),
+            other => Some(MirValue::Integer(text(other)?.chars().count() as i64)),
+        },
+        _ => None,
+    }
+}
+
+/// Whether a short-circuit operator with a constant left operand needs its
+/// right-hand side, or None if the operand is one the executor rejects
+pub(crate) fn needs_right(inst: &MirInstruction) -> Option<bool> {
+    let (left, is_and) = match inst {
+        MirInstruction::AndSC { left, .. } => (left, true),
+        MirInstruction::OrSC { left, .. } => (left, false),
+        _ => return None,
+    };
+    let left = match constant(left)? {
+        MirValue::Boolean(b) => *b,
+        MirValue::Integer(i) => *i != 0,
+        MirValue::Null => false,
+        _ => return None,
+    };
+    Some(left == is_and)
+}
+
+fn constant(value: &MirValue) -> Option<&MirValue> {
+    is_constant(value).then_some(value)
+}
+
+fn integer(value: &MirValue) -> Option<i64> {
+    as_integer(constant(value)?)
+}
+
+/// Text of a constant as the executor renders it in a string
+fn text(value: &MirValue) -> Option<String> {
+    Some(match value {
+        MirValue::String(s) => s.to_string(),
+        MirValue::Integer(i) => i.to_string(),
+        MirValue::Float(f) => f.to_string(),
+        MirValue::Boolean(b) => b.to_string(),
+        MirValue::Null => String::new(),
+        _ => return None,
+    })
+}
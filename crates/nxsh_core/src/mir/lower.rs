@@ -1,52 +1,1869 @@
-//! AST -> MIR Lowering (改良: ClosureCreate 対応)
+//! AST -> MIR Lowering
+//!
+//! 制御構造 (if / while / until / for / case / match / try) は基本ブロックに分割し、
+//! `Jump` / `Branch` で繋ぐ。変数は関数・クロージャ・サブシェル単位のスコープで
+//! レジスタに割り当て、どのスコープにも無い変数はグローバル (`Load` / `Store`) として扱う。
 use super::{MirFunction, MirInstruction, MirProgram, MirRegister, MirValue};
-use nxsh_parser::ast::AstNode;
-use nxsh_parser::ast::BinaryOperator;
+use nxsh_parser::ast::{
+    AssignmentOperator, AstNode, BinaryOperator, BraceElement, GlobElement, GlobPattern, MatchArm,
+    Parameter, ParameterModifier, Pattern, PipeOperator, PostfixOperator, Redirection,
+    RedirectionOperator, RedirectionTarget, TestOperator, TestUnaryOperator, UnaryOperator,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// 変数スコープの種類
+#[derive(PartialEq)]
+enum ScopeKind {
+    /// 関数・クロージャ本体。外側のローカル変数は見えない
+    Function,
+    /// サブシェル (パイプラインの段・バックグラウンドジョブを含む)。
+    /// 外側の変数は読めるが、代入はこのスコープに閉じる
+    Subshell,
+}
+
+struct Scope {
+    kind: ScopeKind,
+    vars: HashMap<String, MirRegister>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind) -> Self {
+        Self {
+            kind,
+            vars: HashMap::new(),
+        }
+    }
+}
+
+/// break / continue の飛び先
+struct LoopTarget {
+    continue_block: u32,
+    break_block: u32,
+    // ループ開始時点の try の深さ (脱出時に閉じる try 領域の数を決める)
+    try_depth: usize,
+}
 
 pub struct Lowerer {
     reg_counter: u32,
-    // 直前に lower 済みの変数 -> レジスタ 対応 (簡易キャプチャ用)
-    var_env: std::collections::HashMap<String, MirRegister>,
-    // クロージャ本体を lowering 中かどうか（短絡命令の生成方針に影響）
-    in_closure: bool,
+    // 内側のスコープほど後ろ。空ならトップレベルで、変数はすべてグローバル
+    scopes: Vec<Scope>,
+    // 代入済みのグローバル変数 (式中の Word や呼び出し先を変数として解決するため)
+    globals: HashSet<String>,
+    loops: Vec<LoopTarget>,
+    try_depth: usize,
+    // 算術式の中では裸の Word も変数参照
+    in_arith: bool,
+    // 文字列リテラル。同じ文字列は一つの Arc<str> を共有する
+    strings: HashSet<Arc<str>>,
+}
+
+impl Lowerer {
+    pub fn new() -> Self {
+        Self {
+            reg_counter: 0,
+            scopes: Vec::new(),
+            globals: HashSet::new(),
+            loops: Vec::new(),
+            try_depth: 0,
+            in_arith: false,
+            strings: HashSet::new(),
+        }
+    }
+}
+
+impl Default for Lowerer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn reg(r: &MirRegister) -> MirValue {
+    MirValue::Register(r.clone())
+}
+
+fn emit(func: &mut MirFunction, block: u32, inst: MirInstruction) {
+    if let Some(b) = func.get_block_mut(block) {
+        b.instructions.push(inst);
+    }
+}
+
+fn link(func: &mut MirFunction, from: u32, to: u32) {
+    if let Some(b) = func.get_block_mut(from) {
+        b.add_successor(to);
+    }
+    if let Some(b) = func.get_block_mut(to) {
+        b.add_predecessor(from);
+    }
+}
+
+fn jump(func: &mut MirFunction, from: u32, to: u32) {
+    emit(func, from, MirInstruction::Jump { target: to });
+    link(func, from, to);
+}
+
+fn branch(func: &mut MirFunction, from: u32, condition: MirRegister, t: u32, f: u32) {
+    emit(
+        func,
+        from,
+        MirInstruction::Branch {
+            condition: MirValue::Register(condition),
+            true_block: t,
+            false_block: f,
+        },
+    );
+    link(func, from, t);
+    link(func, from, f);
+}
+
+/// ブロック末尾が制御を移す命令なら、それ以降の命令には到達しない
+fn is_terminated(func: &MirFunction, block: u32) -> bool {
+    matches!(
+        func.get_block(block).and_then(|b| b.instructions.last()),
+        Some(
+            MirInstruction::Return { .. }
+                | MirInstruction::ClosureReturn { .. }
+                | MirInstruction::Jump { .. }
+                | MirInstruction::Branch { .. }
+                | MirInstruction::Throw { .. }
+                | MirInstruction::Unreachable
+        )
+    )
+}
+
+/// 終端していないブロックに `last` を返す Return を置く
+fn finish(func: &mut MirFunction, block: u32, last: Option<MirRegister>) {
+    if !is_terminated(func, block) {
+        emit(
+            func,
+            block,
+            MirInstruction::Return {
+                value: Some(last.map_or(MirValue::Null, MirValue::Register)),
+            },
+        );
+    }
+}
+
+/// 結果がそのまま関数の戻り値になる呼び出しを TailCall にする。
+/// 後の命令は残すので、実行時にフレームを使い回さなくても同じ結果になる
+fn mark_tail_calls(func: &mut MirFunction) {
+    // 入口から辿れるブロックだけ (クロージャ・サブシェル・ハンドラは別のフレームで動く)
+    let mut seen = HashSet::new();
+    let mut pending = vec![func.entry_block];
+    while let Some(id) = pending.pop() {
+        if seen.insert(id) {
+            if let Some(block) = func.get_block(id) {
+                pending.extend(block.successors.iter().copied());
+            }
+        }
+    }
+    for id in seen {
+        let Some(block) = func.get_block(id) else {
+            continue;
+        };
+        let calls: Vec<usize> = (0..block.instructions.len())
+            .filter(|&i| match &block.instructions[i] {
+                MirInstruction::Call { dest, function, .. }
+                | MirInstruction::ExecuteCommand {
+                    dest,
+                    command: function,
+                    ..
+                } => !function.is_empty() && returns(func, id, i + 1, dest),
+                _ => false,
+            })
+            .collect();
+        let Some(block) = func.get_block_mut(id) else {
+            continue;
+        };
+        for i in calls {
+            let inst = &mut block.instructions[i];
+            if let MirInstruction::Call {
+                dest,
+                function,
+                args,
+            }
+            | MirInstruction::ExecuteCommand {
+                dest,
+                command: function,
+                args,
+            } = std::mem::replace(inst, MirInstruction::Nop)
+            {
+                *inst = MirInstruction::TailCall {
+                    dest,
+                    function,
+                    args,
+                };
+            }
+        }
+    }
+}
+
+/// `block` の `ip` 以降が `value` を (Move で移しながら) そのまま返すか
+fn returns(func: &MirFunction, mut block: u32, mut ip: usize, value: &MirRegister) -> bool {
+    let mut value = value.clone();
+    // 空のループで回り続けないよう辿るブロック数を抑える
+    'blocks: for _ in 0..8 {
+        let Some(current) = func.get_block(block) else {
+            return false;
+        };
+        for inst in current.instructions.iter().skip(ip) {
+            match inst {
+                MirInstruction::Move { dest, src } if *src == value => value = dest.clone(),
+                MirInstruction::Jump { target } => {
+                    block = *target;
+                    ip = 0;
+                    continue 'blocks;
+                }
+                MirInstruction::Return {
+                    value: Some(MirValue::Register(returned)),
+                } => return *returned == value,
+                _ => return false,
+            }
+        }
+        match current.successors.first() {
+            Some(next) => {
+                block = *next;
+                ip = 0;
+            }
+            None => return false,
+        }
+    }
+    false
+}
+
+fn is_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// シェルのグロブを (アンカー無しの) 正規表現に変換する
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::new();
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            '\\' => match chars.next() {
+                Some(n) => out.push_str(&regex::escape(&n.to_string())),
+                None => out.push_str(r"\\"),
+            },
+            '[' => {
+                let rest: String = chars.clone().collect();
+                // 閉じ括弧の無い [ は文字そのもの
+                match rest.find(']') {
+                    Some(end) if end > 0 => {
+                        let class = &rest[..end];
+                        for _ in 0..class.chars().count() + 1 {
+                            chars.next();
+                        }
+                        out.push('[');
+                        let class = match class.strip_prefix(['!', '^']) {
+                            Some(negated) => {
+                                out.push('^');
+                                negated
+                            }
+                            None => class,
+                        };
+                        for ch in class.chars() {
+                            if matches!(ch, '\\' | '[' | ']' | '^' | '&' | '~') {
+                                out.push('\\');
+                            }
+                            out.push(ch);
+                        }
+                        out.push(']');
+                    }
+                    _ => out.push_str(r"\["),
+                }
+            }
+            _ => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out
+}
+
+/// パーサが分解したグロブを正規表現に変換する
+fn glob_pattern_regex(pattern: &GlobPattern) -> String {
+    let mut out = String::new();
+    for element in &pattern.elements {
+        match element {
+            GlobElement::Literal(text) => out.push_str(&regex::escape(text)),
+            GlobElement::Wildcard => out.push_str(".*"),
+            GlobElement::SingleChar => out.push('.'),
+            GlobElement::CharacterClass { negated, ranges } => {
+                out.push('[');
+                if *negated {
+                    out.push('^');
+                }
+                for range in ranges {
+                    out.push_str(&regex::escape(&range.start.to_string()));
+                    if let Some(end) = range.end {
+                        out.push('-');
+                        out.push_str(&regex::escape(&end.to_string()));
+                    }
+                }
+                out.push(']');
+            }
+            GlobElement::BraceExpansion(alternatives) => {
+                let alternatives: Vec<String> =
+                    alternatives.iter().map(|a| regex::escape(a)).collect();
+                out.push_str(&format!("(?:{})", alternatives.join("|")));
+            }
+        }
+    }
+    out
+}
+
+/// パーサが分解したグロブを元の文字列に戻す (一致が無いときはそのまま引数になる)
+fn glob_pattern_text(pattern: &GlobPattern) -> String {
+    let mut out = String::new();
+    for element in &pattern.elements {
+        match element {
+            GlobElement::Literal(text) => out.push_str(text),
+            GlobElement::Wildcard => out.push('*'),
+            GlobElement::SingleChar => out.push('?'),
+            GlobElement::CharacterClass { negated, ranges } => {
+                out.push('[');
+                if *negated {
+                    out.push('!');
+                }
+                for range in ranges {
+                    out.push(range.start);
+                    if let Some(end) = range.end {
+                        out.push('-');
+                        out.push(end);
+                    }
+                }
+                out.push(']');
+            }
+            GlobElement::BraceExpansion(alternatives) => {
+                out.push_str(&format!("{{{}}}", alternatives.join(",")));
+            }
+        }
+    }
+    out
+}
+
+/// `{a,b}` / `{1..5..2}` をコンパイル時に展開する
+fn expand_braces(elements: &[BraceElement]) -> Vec<String> {
+    let mut words = Vec::new();
+    for element in elements {
+        match element {
+            BraceElement::Literal(text) => words.push(text.to_string()),
+            BraceElement::Sequence { start, end, step } => {
+                let step = step.unwrap_or(1).unsigned_abs().max(1) as usize;
+                if start <= end {
+                    words.extend((*start..=*end).step_by(step).map(|n| n.to_string()));
+                } else {
+                    words.extend((*end..=*start).rev().step_by(step).map(|n| n.to_string()));
+                }
+            }
+        }
+    }
+    words
+}
+
+/// 型パターンの型名を `typeof` の返す名前に揃える
+fn canonical_type(type_name: &str) -> String {
+    let lower = type_name.to_ascii_lowercase();
+    match lower.as_str() {
+        "int" | "integer" | "i64" | "number" => "int",
+        "str" | "string" => "string",
+        "bool" | "boolean" => "bool",
+        "float" | "f64" | "double" => "float",
+        "array" | "list" | "vec" => "array",
+        "object" | "map" | "dict" => "object",
+        "closure" | "fn" | "function" => "closure",
+        "null" | "none" | "nil" => "null",
+        _ => return lower,
+    }
+    .to_string()
 }
 
-impl Lowerer {
-    pub fn new() -> Self {
-        Self {
-            reg_counter: 0,
-            var_env: std::collections::HashMap::new(),
-            in_closure: false,
+fn test_flag(operator: &TestUnaryOperator) -> &'static str {
+    use TestUnaryOperator::*;
+    match operator {
+        FileExists => "-e",
+        FileRegular => "-f",
+        FileDirectory => "-d",
+        FileSymlink => "-L",
+        FileReadable => "-r",
+        FileWritable => "-w",
+        FileExecutable => "-x",
+        FileNonEmpty => "-s",
+        FileBlockDevice => "-b",
+        FileCharDevice => "-c",
+        FileFifo => "-p",
+        FileSocket => "-S",
+        FileSticky => "-k",
+        FileSetgid => "-g",
+        FileSetuid => "-u",
+        FileOwned => "-O",
+        FileGroupOwned => "-G",
+        FileModified => "-N",
+        FileTty => "-t",
+        StringEmpty => "-z",
+        StringNonEmpty => "-n",
+        VariableSet => "-v",
+        VariableArray => "-a",
+    }
+}
+
+impl Lowerer {
+    fn fresh_reg(&mut self) -> MirRegister {
+        let id = self.reg_counter;
+        self.reg_counter += 1;
+        MirRegister::new(id)
+    }
+
+    pub fn lower_program(mut self, ast: &AstNode) -> MirProgram {
+        let mut prog = MirProgram::new();
+        let mut func = MirFunction::new("main".to_string(), Vec::new());
+        let mut current = func.entry_block;
+        let last = self.lower_node_prog(ast, &mut prog, &mut func, &mut current);
+        finish(&mut func, current, last);
+        func.register_count = self.reg_counter;
+        prog.add_function(func);
+        // 関数定義が先に登録されていても入口は main
+        prog.main_function = Some("main".to_string());
+        prog
+    }
+
+    /// `build(dest)` を `block` に追加して dest を返す
+    fn op(
+        &mut self,
+        func: &mut MirFunction,
+        block: u32,
+        build: impl FnOnce(MirRegister) -> MirInstruction,
+    ) -> MirRegister {
+        let dest = self.fresh_reg();
+        emit(func, block, build(dest.clone()));
+        dest
+    }
+
+    fn load(&mut self, func: &mut MirFunction, block: u32, value: MirValue) -> MirRegister {
+        self.op(func, block, |dest| MirInstruction::LoadImmediate {
+            dest,
+            value,
+        })
+    }
+
+    /// 文字列リテラルの値 (既出の文字列なら同じ Arc<str> を返す)
+    fn intern(&mut self, text: &str) -> MirValue {
+        let shared = match self.strings.get(text) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared: Arc<str> = Arc::from(text);
+                self.strings.insert(shared.clone());
+                shared
+            }
+        };
+        MirValue::String(shared)
+    }
+
+    /// 値を生まないノードは Null として扱う
+    fn lower_value(
+        &mut self,
+        node: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        match self.lower_node_prog(node, prog, func, current) {
+            Some(r) => r,
+            None => self.load(func, *current, MirValue::Null),
+        }
+    }
+
+    /// コマンド引数: 裸の Word は変数ではなく文字列
+    fn lower_arg(
+        &mut self,
+        node: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        match node {
+            AstNode::Word(word) => {
+                let word = self.intern(word);
+                self.load(func, *current, word)
+            }
+            _ => self.lower_value(node, prog, func, current),
+        }
+    }
+
+    fn lower_seq(
+        &mut self,
+        stmts: &[AstNode],
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> Option<MirRegister> {
+        let mut last = None;
+        for stmt in stmts {
+            // return / break / exit の後ろには到達しない
+            if is_terminated(func, *current) {
+                break;
+            }
+            if let Some(r) = self.lower_node_prog(stmt, prog, func, current) {
+                last = Some(r);
+            }
+        }
+        last
+    }
+
+    fn stringify(
+        &mut self,
+        value: &MirRegister,
+        func: &mut MirFunction,
+        block: u32,
+    ) -> MirRegister {
+        let parts = vec![reg(value)];
+        self.op(func, block, |dest| MirInstruction::Concat { dest, parts })
+    }
+
+    fn truthy(&mut self, value: &MirRegister, func: &mut MirFunction, block: u32) -> MirRegister {
+        let operand = reg(value);
+        let not = self.op(func, block, |dest| MirInstruction::Not { dest, operand });
+        self.op(func, block, |dest| MirInstruction::Not {
+            dest,
+            operand: MirValue::Register(not),
+        })
+    }
+
+    fn both(
+        &mut self,
+        a: &MirRegister,
+        b: &MirRegister,
+        func: &mut MirFunction,
+        block: u32,
+    ) -> MirRegister {
+        let (left, right) = (reg(a), reg(b));
+        self.op(func, block, |dest| MirInstruction::And {
+            dest,
+            left,
+            right,
+        })
+    }
+
+    fn both_opt(
+        &mut self,
+        a: Option<MirRegister>,
+        b: MirRegister,
+        func: &mut MirFunction,
+        block: u32,
+    ) -> MirRegister {
+        match a {
+            Some(a) => self.both(&a, &b, func, block),
+            None => b,
+        }
+    }
+
+    /// 値の終了ステータスが 0 なら true
+    fn succeeded(
+        &mut self,
+        value: &MirRegister,
+        func: &mut MirFunction,
+        block: u32,
+    ) -> MirRegister {
+        let value = reg(value);
+        let status = self.op(func, block, |dest| MirInstruction::Status { dest, value });
+        self.op(func, block, |dest| MirInstruction::Equal {
+            dest,
+            left: MirValue::Register(status),
+            right: MirValue::Integer(0),
+        })
+    }
+
+    /// 条件として実行し、成功したかどうかの Boolean を返す
+    fn lower_condition(
+        &mut self,
+        node: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let value = self.lower_value(node, prog, func, current);
+        // (( expr )) は値が 0 以外なら成功
+        let value = if matches!(node, AstNode::ArithmeticExpansion { .. }) {
+            self.truthy(&value, func, *current)
+        } else {
+            value
+        };
+        self.succeeded(&value, func, *current)
+    }
+
+    // --- 変数 ---
+
+    fn define(&mut self, name: &str) -> MirRegister {
+        let r = self.fresh_reg();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.vars.insert(name.to_string(), r.clone());
+        }
+        r
+    }
+
+    fn lookup(&self, name: &str) -> Option<MirRegister> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(r) = scope.vars.get(name) {
+                return Some(r.clone());
+            }
+            if scope.kind == ScopeKind::Function {
+                break;
+            }
+        }
+        None
+    }
+
+    fn in_subshell(&self) -> bool {
+        // Only the innermost scope decides
+        self.scopes
+            .last()
+            .is_some_and(|scope| scope.kind == ScopeKind::Subshell)
+    }
+
+    fn read_var(&mut self, name: &str, func: &mut MirFunction, block: u32) -> MirRegister {
+        if let Some(r) = self.lookup(name) {
+            return r;
+        }
+        let source = name.to_string();
+        self.op(func, block, |dest| MirInstruction::Load { dest, source })
+    }
+
+    fn assign(
+        &mut self,
+        name: &str,
+        value: MirRegister,
+        is_local: bool,
+        func: &mut MirFunction,
+        block: u32,
+    ) {
+        let home = if is_local && !self.scopes.is_empty() {
+            let existing = self.scopes.last().and_then(|s| s.vars.get(name).cloned());
+            Some(existing.unwrap_or_else(|| self.define(name)))
+        } else if let Some(r) = self.lookup(name) {
+            Some(r)
+        } else if self.in_subshell() {
+            Some(self.define(name))
+        } else {
+            None
+        };
+        match home {
+            Some(home) if home == value => {}
+            Some(home) => emit(
+                func,
+                block,
+                MirInstruction::Move {
+                    dest: home,
+                    src: value,
+                },
+            ),
+            None => {
+                self.globals.insert(name.to_string());
+                emit(
+                    func,
+                    block,
+                    MirInstruction::Store {
+                        dest: name.to_string(),
+                        value: MirValue::Register(value),
+                    },
+                );
+            }
+        }
+    }
+
+    /// `target` が Null なら `default` を評価して入れる
+    fn default_to(
+        &mut self,
+        target: &MirRegister,
+        default: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) {
+        let left = reg(target);
+        let unset = self.op(func, *current, |dest| MirInstruction::Equal {
+            dest,
+            left,
+            right: MirValue::Null,
+        });
+        let (set_block, join) = (func.create_block(), func.create_block());
+        branch(func, *current, unset, set_block, join);
+        *current = set_block;
+        let value = self.lower_value(default, prog, func, current);
+        emit(
+            func,
+            *current,
+            MirInstruction::Move {
+                dest: target.clone(),
+                src: value,
+            },
+        );
+        jump(func, *current, join);
+        *current = join;
+    }
+
+    // --- 関数・クロージャ・サブシェル ---
+
+    /// 関数を独立した MirFunction として登録する。引数はレジスタ 0..n に置かれる
+    fn lower_function(
+        &mut self,
+        name: &str,
+        params: &[Parameter],
+        body: &AstNode,
+        prog: &mut MirProgram,
+    ) {
+        let param_names = params.iter().map(|p| p.name.to_string()).collect();
+        let mut f = MirFunction::new(name.to_string(), param_names);
+        let mut current = f.entry_block;
+        // ネスト関数は独立した Lowerer で lower する (グローバル変数の知識だけ引き継ぐ)
+        let mut nested = Lowerer::new();
+        nested.globals = self.globals.clone();
+        nested.strings = std::mem::take(&mut self.strings);
+        nested.scopes.push(Scope::new(ScopeKind::Function));
+        let regs: Vec<MirRegister> = params.iter().map(|p| nested.define(p.name)).collect();
+        for (p, r) in params.iter().zip(&regs) {
+            if let Some(default) = &p.default {
+                nested.default_to(r, default, prog, &mut f, &mut current);
+            }
+        }
+        let last = nested.lower_node_prog(body, prog, &mut f, &mut current);
+        finish(&mut f, current, last);
+        mark_tail_calls(&mut f);
+        f.register_count = nested.reg_counter;
+        self.strings = nested.strings;
+        prog.add_function(f);
+    }
+
+    /// サブシェルとして別ブロックに lower する (バックグラウンドジョブ・パイプラインの段・コマンド置換)
+    fn lower_detached(
+        &mut self,
+        node: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+    ) -> u32 {
+        let block = func.create_block();
+        let saved_loops = std::mem::take(&mut self.loops);
+        let saved_depth = std::mem::replace(&mut self.try_depth, 0);
+        self.scopes.push(Scope::new(ScopeKind::Subshell));
+        let mut body = block;
+        let last = self.lower_node_prog(node, prog, func, &mut body);
+        finish(func, body, last);
+        self.scopes.pop();
+        self.loops = saved_loops;
+        self.try_depth = saved_depth;
+        block
+    }
+
+    fn lower_background(
+        &mut self,
+        node: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let block = self.lower_detached(node, prog, func);
+        self.op(func, *current, |dest| MirInstruction::Background {
+            dest,
+            block,
+        })
+    }
+
+    fn lower_closure(
+        &mut self,
+        params: &[Parameter],
+        body: &AstNode,
+        captures: &[&str],
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        // 捕捉する値は外側で評価しておく (グローバルなら Load)
+        let capture_vals: Vec<MirValue> = captures
+            .iter()
+            .map(|c| reg(&self.read_var(c, func, *current)))
+            .collect();
+        let body_block = func.create_block();
+        let saved_loops = std::mem::take(&mut self.loops);
+        let saved_depth = std::mem::replace(&mut self.try_depth, 0);
+        self.scopes.push(Scope::new(ScopeKind::Function));
+        let param_regs: Vec<MirRegister> = params.iter().map(|p| self.define(p.name)).collect();
+        let capture_regs: Vec<MirRegister> = captures.iter().map(|c| self.define(c)).collect();
+        let mut body_current = body_block;
+        for (p, r) in params.iter().zip(&param_regs) {
+            if let Some(default) = &p.default {
+                self.default_to(r, default, prog, func, &mut body_current);
+            }
+        }
+        let last = self.lower_node_prog(body, prog, func, &mut body_current);
+        finish(func, body_current, last);
+        self.scopes.pop();
+        self.loops = saved_loops;
+        self.try_depth = saved_depth;
+        let param_names = params.iter().map(|p| p.name.to_string()).collect();
+        self.op(func, *current, |dest| MirInstruction::ClosureCreate {
+            dest,
+            func_block: body_block,
+            captures: capture_vals,
+            capture_regs,
+            param_regs,
+            param_names,
+        })
+    }
+
+    // --- コマンド・パイプライン ---
+
+    fn lower_redirect(
+        &mut self,
+        redirection: &Redirection,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) {
+        use RedirectionOperator::*;
+        let (operator, fd) = match redirection.operator {
+            Output => (">", 1),
+            OutputAppend => (">>", 1),
+            Input => ("<", 0),
+            InputOutput => ("<>", 0),
+            OutputBoth => ("&>", 1),
+            OutputBothAppend => ("&>>", 1),
+            HereDocument => ("<<", 0),
+            HereString => ("<<<", 0),
+            DuplicateInput => ("<&", 0),
+            DuplicateOutput => (">&", 1),
+        };
+        let target = match &redirection.target {
+            RedirectionTarget::File(path) => reg(&self.lower_arg(path, prog, func, current)),
+            RedirectionTarget::FileDescriptor(n) => MirValue::Integer(*n as i64),
+            RedirectionTarget::Close => MirValue::string("-"),
+            RedirectionTarget::HereDoc { content, .. } => MirValue::string(*content),
+        };
+        emit(
+            func,
+            *current,
+            MirInstruction::RedirectPush {
+                fd: redirection.fd.unwrap_or(fd),
+                operator: operator.to_string(),
+                target,
+            },
+        );
+    }
+
+    fn lower_command(
+        &mut self,
+        name: &AstNode,
+        args: &[AstNode],
+        redirections: &[Redirection],
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        for redirection in redirections {
+            self.lower_redirect(redirection, prog, func, current);
+        }
+        // 名前が式ならその値をコマンド名として先頭の引数で渡す
+        let (command, mut arg_vals) = match name {
+            AstNode::Word(word) | AstNode::StringLiteral { value: word, .. } => {
+                (word.to_string(), Vec::new())
+            }
+            dynamic => {
+                let name = self.lower_arg(dynamic, prog, func, current);
+                (String::new(), vec![reg(&name)])
+            }
+        };
+        for a in args {
+            let value = self.lower_arg(a, prog, func, current);
+            arg_vals.push(reg(&value));
+        }
+        let dest = self.op(func, *current, |dest| MirInstruction::ExecuteCommand {
+            dest,
+            command,
+            args: arg_vals,
+        });
+        for _ in redirections {
+            emit(func, *current, MirInstruction::RedirectPop);
+        }
+        dest
+    }
+
+    /// `|` で繋がった段をパイプラインとして実行する
+    fn lower_stages(
+        &mut self,
+        stages: &[&AstNode],
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        if let [only] = stages {
+            return self.lower_value(only, prog, func, current);
+        }
+        emit(func, *current, MirInstruction::PipelineStart);
+        for stage in stages {
+            let command = match stage {
+                AstNode::Command {
+                    name,
+                    args,
+                    redirections,
+                    background: false,
+                } if redirections.is_empty() && matches!(**name, AstNode::Word(_)) => {
+                    let mut elements = vec![reg(&self.lower_arg(name, prog, func, current))];
+                    for a in args {
+                        elements.push(reg(&self.lower_arg(a, prog, func, current)));
+                    }
+                    self.op(func, *current, |dest| MirInstruction::MakeArray {
+                        dest,
+                        elements,
+                    })
+                }
+                AstNode::SimpleCommand { name, args } => {
+                    let elements = std::iter::once(name)
+                        .chain(args)
+                        .map(|w| self.intern(w))
+                        .collect();
+                    self.op(func, *current, |dest| MirInstruction::MakeArray {
+                        dest,
+                        elements,
+                    })
+                }
+                // 複合コマンドの段は引数なしのクロージャとして実行する
+                compound => {
+                    let func_block = self.lower_detached(compound, prog, func);
+                    self.op(func, *current, |dest| MirInstruction::ClosureCreate {
+                        dest,
+                        func_block,
+                        captures: Vec::new(),
+                        capture_regs: Vec::new(),
+                        param_regs: Vec::new(),
+                        param_names: Vec::new(),
+                    })
+                }
+            };
+            emit(func, *current, MirInstruction::PipelineAdd { command });
+        }
+        self.op(func, *current, |dest| MirInstruction::PipelineExec { dest })
+    }
+
+    fn lower_pipeline(
+        &mut self,
+        elements: &[AstNode],
+        operators: &[PipeOperator],
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        // `|` で繋がった段をまとめ、`&&` / `||` / `;` / `&` で区切る
+        // (直前の演算子, 段, バックグラウンドか)
+        let mut segments: Vec<(Option<&PipeOperator>, Vec<&AstNode>, bool)> = Vec::new();
+        let mut joiner: Option<&PipeOperator> = None;
+        for (i, element) in elements.iter().enumerate() {
+            let piped = matches!(
+                joiner,
+                Some(
+                    PipeOperator::Pipe
+                        | PipeOperator::ObjectPipe
+                        | PipeOperator::ObjectPipeParallel
+                )
+            );
+            match segments.last_mut() {
+                Some((_, stages, _)) if piped => stages.push(element),
+                _ => segments.push((joiner, vec![element], false)),
+            }
+            joiner = operators.get(i);
+            if joiner == Some(&PipeOperator::Background) {
+                if let Some(segment) = segments.last_mut() {
+                    segment.2 = true;
+                }
+            }
+        }
+        let result = self.load(func, *current, MirValue::Null);
+        for (before, stages, background) in segments {
+            // && / || は直前までの結果の終了ステータスで実行するか決める
+            let join = match before {
+                Some(op @ (PipeOperator::LogicalAnd | PipeOperator::LogicalOr)) => {
+                    let ok = self.succeeded(&result, func, *current);
+                    let (run, join) = (func.create_block(), func.create_block());
+                    if *op == PipeOperator::LogicalAnd {
+                        branch(func, *current, ok, run, join);
+                    } else {
+                        branch(func, *current, ok, join, run);
+                    }
+                    *current = run;
+                    Some(join)
+                }
+                _ => None,
+            };
+            let value = if background {
+                let node = match stages.as_slice() {
+                    [only] => (*only).clone(),
+                    _ => AstNode::Pipeline {
+                        elements: stages.iter().map(|s| (*s).clone()).collect(),
+                        operators: vec![PipeOperator::Pipe; stages.len() - 1],
+                    },
+                };
+                self.lower_background(&node, prog, func, current)
+            } else {
+                self.lower_stages(&stages, prog, func, current)
+            };
+            if !is_terminated(func, *current) {
+                emit(
+                    func,
+                    *current,
+                    MirInstruction::Move {
+                        dest: result.clone(),
+                        src: value,
+                    },
+                );
+                if let Some(join) = join {
+                    jump(func, *current, join);
+                }
+            }
+            if let Some(join) = join {
+                *current = join;
+            }
+        }
+        result
+    }
+
+    // --- 制御構造 ---
+
+    /// 分岐の腕を lower し、値を `result` に入れて `join` へ合流する
+    fn lower_arm(
+        &mut self,
+        body: &AstNode,
+        result: &MirRegister,
+        join: u32,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) {
+        let value = self.lower_node_prog(body, prog, func, current);
+        if !is_terminated(func, *current) {
+            if let Some(v) = value {
+                emit(
+                    func,
+                    *current,
+                    MirInstruction::Move {
+                        dest: result.clone(),
+                        src: v,
+                    },
+                );
+            }
+            jump(func, *current, join);
+        }
+    }
+
+    /// ループ本体を lower し、末尾から `continue_block` へ戻る
+    fn lower_loop_body(
+        &mut self,
+        body: &AstNode,
+        body_block: u32,
+        continue_block: u32,
+        break_block: u32,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+    ) {
+        self.loops.push(LoopTarget {
+            continue_block,
+            break_block,
+            try_depth: self.try_depth,
+        });
+        let mut current = body_block;
+        self.lower_node_prog(body, prog, func, &mut current);
+        if !is_terminated(func, current) {
+            jump(func, current, continue_block);
+        }
+        self.loops.pop();
+    }
+
+    fn lower_match(
+        &mut self,
+        expr: &AstNode,
+        arms: &[MatchArm],
+        default_arm: Option<&AstNode>,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let value = self.lower_value(expr, prog, func, current);
+        let result = self.load(func, *current, MirValue::Null);
+        let join = func.create_block();
+        for arm in arms {
+            let mut matched =
+                self.lower_pattern(&arm.pattern, &value, false, false, prog, func, current);
+            if let Some(guard) = &arm.guard {
+                let guard = self.lower_value(guard, prog, func, current);
+                let guard = self.truthy(&guard, func, *current);
+                matched = Some(self.both_opt(matched, guard, func, *current));
+            }
+            let mut body = func.create_block();
+            match matched {
+                Some(ok) => {
+                    let next = func.create_block();
+                    branch(func, *current, ok, body, next);
+                    self.lower_arm(&arm.body, &result, join, prog, func, &mut body);
+                    *current = next;
+                }
+                None => {
+                    // 常に一致する腕より後ろには到達しない
+                    jump(func, *current, body);
+                    self.lower_arm(&arm.body, &result, join, prog, func, &mut body);
+                    *current = func.create_block();
+                }
+            }
+        }
+        match default_arm {
+            Some(default) => self.lower_arm(default, &result, join, prog, func, current),
+            None => jump(func, *current, join),
+        }
+        *current = join;
+        result
+    }
+
+    /// `value` を `pattern` と照合し、一致したかどうかの Boolean を返す (常に一致するなら None)。
+    /// 束縛は照合しながら代入する。`glob` なら case 文として文字列をグロブで照合する
+    #[allow(clippy::too_many_arguments)]
+    fn lower_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &MirRegister,
+        glob: bool,
+        is_local: bool,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> Option<MirRegister> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Placeholder => None,
+            Pattern::Literal(text) if glob => {
+                Some(self.glob_match(value, &glob_to_regex(text), func, *current))
+            }
+            Pattern::Literal(text) => {
+                let left = reg(&self.stringify(value, func, *current));
+                let right = self.intern(text);
+                Some(self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left,
+                    right,
+                }))
+            }
+            Pattern::Glob(glob_pattern) => {
+                Some(self.glob_match(value, &glob_pattern_regex(glob_pattern), func, *current))
+            }
+            Pattern::Variable(name) if glob => {
+                let expected = self.read_var(name, func, *current);
+                let right = reg(&self.stringify(&expected, func, *current));
+                let left = reg(&self.stringify(value, func, *current));
+                Some(self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left,
+                    right,
+                }))
+            }
+            Pattern::Variable(name) => {
+                self.assign(name, value.clone(), is_local, func, *current);
+                None
+            }
+            Pattern::Range { start, end } => {
+                let (above, below) = match (start.parse::<i64>(), end.parse::<i64>()) {
+                    (Ok(low), Ok(high)) => {
+                        let subject = reg(value);
+                        let above = self.op(func, *current, |dest| MirInstruction::GreaterEqual {
+                            dest,
+                            left: subject.clone(),
+                            right: MirValue::Integer(low),
+                        });
+                        let below = self.op(func, *current, |dest| MirInstruction::LessEqual {
+                            dest,
+                            left: subject,
+                            right: MirValue::Integer(high),
+                        });
+                        (above, below)
+                    }
+                    // 文字の範囲は文字列として比較する
+                    _ => {
+                        let subject = reg(&self.stringify(value, func, *current));
+                        let above = self.op(func, *current, |dest| MirInstruction::Compare {
+                            dest,
+                            left: subject.clone(),
+                            right: MirValue::string(*start),
+                            op: "ge".to_string(),
+                        });
+                        let below = self.op(func, *current, |dest| MirInstruction::Compare {
+                            dest,
+                            left: subject,
+                            right: MirValue::string(*end),
+                            op: "le".to_string(),
+                        });
+                        (above, below)
+                    }
+                };
+                Some(self.both(&above, &below, func, *current))
+            }
+            Pattern::Alternative(alternatives) | Pattern::Or(alternatives) => {
+                let mut any: Option<MirRegister> = None;
+                for alternative in alternatives {
+                    // 常に一致する候補があれば全体も常に一致する
+                    let matched = self.lower_pattern(
+                        alternative,
+                        value,
+                        glob,
+                        is_local,
+                        prog,
+                        func,
+                        current,
+                    )?;
+                    any = Some(match any {
+                        Some(prev) => {
+                            let (left, right) = (reg(&prev), reg(&matched));
+                            self.op(func, *current, |dest| MirInstruction::Or {
+                                dest,
+                                left,
+                                right,
+                            })
+                        }
+                        None => matched,
+                    });
+                }
+                Some(any.unwrap_or_else(|| self.load(func, *current, MirValue::Boolean(false))))
+            }
+            Pattern::Tuple(items) | Pattern::Array(items) => {
+                let array = reg(value);
+                let len = self.op(func, *current, |dest| MirInstruction::ArrayLength {
+                    dest,
+                    array: array.clone(),
+                });
+                let count = MirValue::Integer(items.len() as i64);
+                let mut ok = self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left: MirValue::Register(len),
+                    right: count,
+                });
+                for (i, item) in items.iter().enumerate() {
+                    let element = self.op(func, *current, |dest| MirInstruction::ArrayGet {
+                        dest,
+                        array: array.clone(),
+                        index: MirValue::Integer(i as i64),
+                    });
+                    if let Some(m) =
+                        self.lower_pattern(item, &element, glob, is_local, prog, func, current)
+                    {
+                        ok = self.both(&ok, &m, func, *current);
+                    }
+                }
+                Some(ok)
+            }
+            Pattern::ArraySlice {
+                before,
+                rest,
+                after,
+            } => {
+                let array = reg(value);
+                let len = self.op(func, *current, |dest| MirInstruction::ArrayLength {
+                    dest,
+                    array: array.clone(),
+                });
+                let fixed = (before.len() + after.len()) as i64;
+                let mut ok = self.op(func, *current, |dest| MirInstruction::GreaterEqual {
+                    dest,
+                    left: reg(&len),
+                    right: MirValue::Integer(fixed),
+                });
+                for (i, item) in before.iter().enumerate() {
+                    let element = self.op(func, *current, |dest| MirInstruction::ArrayGet {
+                        dest,
+                        array: array.clone(),
+                        index: MirValue::Integer(i as i64),
+                    });
+                    if let Some(m) =
+                        self.lower_pattern(item, &element, glob, is_local, prog, func, current)
+                    {
+                        ok = self.both(&ok, &m, func, *current);
+                    }
+                }
+                for (j, item) in after.iter().enumerate() {
+                    let index = self.op(func, *current, |dest| MirInstruction::Sub {
+                        dest,
+                        left: reg(&len),
+                        right: MirValue::Integer((after.len() - j) as i64),
+                    });
+                    let element = self.op(func, *current, |dest| MirInstruction::ArrayGet {
+                        dest,
+                        array: array.clone(),
+                        index: MirValue::Register(index),
+                    });
+                    if let Some(m) =
+                        self.lower_pattern(item, &element, glob, is_local, prog, func, current)
+                    {
+                        ok = self.both(&ok, &m, func, *current);
+                    }
+                }
+                if let Some(rest) = rest {
+                    let count = self.op(func, *current, |dest| MirInstruction::Sub {
+                        dest,
+                        left: reg(&len),
+                        right: MirValue::Integer(fixed),
+                    });
+                    let slice = self.op(func, *current, |dest| MirInstruction::Substring {
+                        dest,
+                        string: array.clone(),
+                        start: MirValue::Integer(before.len() as i64),
+                        length: Some(MirValue::Register(count)),
+                    });
+                    if let Some(m) =
+                        self.lower_pattern(rest, &slice, glob, is_local, prog, func, current)
+                    {
+                        ok = self.both(&ok, &m, func, *current);
+                    }
+                }
+                Some(ok)
+            }
+            Pattern::Object { fields, .. } => {
+                let mut ok: Option<MirRegister> = None;
+                for field in fields {
+                    let object = reg(value);
+                    let key = field.key.to_string();
+                    let field_value = self.op(func, *current, |dest| MirInstruction::ObjectGet {
+                        dest,
+                        object,
+                        field: key,
+                    });
+                    match &field.default {
+                        Some(default) => {
+                            self.default_to(&field_value, default, prog, func, current)
+                        }
+                        None => {
+                            let left = reg(&field_value);
+                            let present =
+                                self.op(func, *current, |dest| MirInstruction::NotEqual {
+                                    dest,
+                                    left,
+                                    right: MirValue::Null,
+                                });
+                            ok = Some(self.both_opt(ok, present, func, *current));
+                        }
+                    }
+                    let matched = match &field.pattern {
+                        Some(p) => {
+                            self.lower_pattern(p, &field_value, glob, is_local, prog, func, current)
+                        }
+                        None => {
+                            self.assign(field.key, field_value, is_local, func, *current);
+                            None
+                        }
+                    };
+                    if let Some(m) = matched {
+                        ok = Some(self.both_opt(ok, m, func, *current));
+                    }
+                }
+                ok
+            }
+            Pattern::Type { type_name, inner } => {
+                let args = vec![reg(value)];
+                let actual = self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "typeof".to_string(),
+                    args,
+                });
+                let expected = MirValue::string(canonical_type(type_name));
+                let ok = self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left: MirValue::Register(actual),
+                    right: expected,
+                });
+                match inner {
+                    Some(inner) => {
+                        match self.lower_pattern(inner, value, glob, is_local, prog, func, current)
+                        {
+                            Some(m) => Some(self.both(&ok, &m, func, *current)),
+                            None => Some(ok),
+                        }
+                    }
+                    None => Some(ok),
+                }
+            }
+            Pattern::Guard { pattern, condition } => {
+                let matched =
+                    self.lower_pattern(pattern, value, glob, is_local, prog, func, current);
+                let condition = self.lower_value(condition, prog, func, current);
+                let condition = self.truthy(&condition, func, *current);
+                Some(self.both_opt(matched, condition, func, *current))
+            }
+            Pattern::Binding { name, pattern } => {
+                self.assign(name, value.clone(), is_local, func, *current);
+                self.lower_pattern(pattern, value, glob, is_local, prog, func, current)
+            }
+            Pattern::Reference(inner) => {
+                self.lower_pattern(inner, value, glob, is_local, prog, func, current)
+            }
+        }
+    }
+
+    fn glob_match(
+        &mut self,
+        value: &MirRegister,
+        regex: &str,
+        func: &mut MirFunction,
+        block: u32,
+    ) -> MirRegister {
+        let subject = reg(&self.stringify(value, func, block));
+        let pattern = MirValue::string(format!("^(?:{regex})$"));
+        self.op(func, block, |dest| MirInstruction::RegexMatch {
+            dest,
+            value: subject,
+            pattern,
+            not: false,
+        })
+    }
+
+    fn lower_try(
+        &mut self,
+        body: &AstNode,
+        catch_clauses: &[nxsh_parser::ast::CatchClause],
+        finally_clause: Option<&AstNode>,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let result = self.load(func, *current, MirValue::Null);
+        let (handler, after) = (func.create_block(), func.create_block());
+        self.lower_guarded(body, handler, &result, after, prog, func, current);
+        // catch 節に型の指定は無いので、最初の節がすべてのエラーを受ける
+        match catch_clauses.first() {
+            Some(clause) => {
+                let mut handling = handler;
+                let error = self.op(func, handling, |dest| MirInstruction::Caught { dest });
+                if let Some(variable) = clause.variable {
+                    self.assign(variable, error, false, func, handling);
+                }
+                match finally_clause {
+                    // catch 節で起きたエラーも finally を実行してから投げ直す
+                    Some(_) => {
+                        let rethrow = func.create_block();
+                        self.lower_guarded(
+                            &clause.body,
+                            rethrow,
+                            &result,
+                            after,
+                            prog,
+                            func,
+                            &mut handling,
+                        );
+                        self.lower_rethrow(finally_clause, rethrow, prog, func);
+                    }
+                    None => self.lower_arm(&clause.body, &result, after, prog, func, &mut handling),
+                }
+            }
+            // catch が無ければ finally を実行してから投げ直す
+            None => self.lower_rethrow(finally_clause, handler, prog, func),
+        }
+        *current = after;
+        if let Some(finally) = finally_clause {
+            self.lower_node_prog(finally, prog, func, current);
+        }
+        result
+    }
+
+    /// `handler` を try ハンドラにして `body` を lower し、正常に抜けたら
+    /// 値を `result` に入れて `join` へ進む
+    #[allow(clippy::too_many_arguments)]
+    fn lower_guarded(
+        &mut self,
+        body: &AstNode,
+        handler: u32,
+        result: &MirRegister,
+        join: u32,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) {
+        emit(
+            func,
+            *current,
+            MirInstruction::TryBegin {
+                handler_block: handler,
+            },
+        );
+        self.try_depth += 1;
+        let value = self.lower_node_prog(body, prog, func, current);
+        self.try_depth -= 1;
+        if !is_terminated(func, *current) {
+            emit(func, *current, MirInstruction::TryEnd);
+            if let Some(v) = value {
+                emit(
+                    func,
+                    *current,
+                    MirInstruction::Move {
+                        dest: result.clone(),
+                        src: v,
+                    },
+                );
+            }
+            jump(func, *current, join);
+        }
+    }
+
+    /// ハンドラ `block` で受けたエラーを finally の後で投げ直す
+    fn lower_rethrow(
+        &mut self,
+        finally: Option<&AstNode>,
+        block: u32,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+    ) {
+        let mut block = block;
+        let error = self.op(func, block, |dest| MirInstruction::Caught { dest });
+        if let Some(finally) = finally {
+            self.lower_node_prog(finally, prog, func, &mut block);
+        }
+        if !is_terminated(func, block) {
+            emit(func, block, MirInstruction::Throw { value: reg(&error) });
         }
     }
-}
 
-impl Default for Lowerer {
-    fn default() -> Self {
-        Self::new()
+    // --- 展開 ---
+
+    fn lower_modifier(
+        &mut self,
+        name: &str,
+        value: MirRegister,
+        modifier: &ParameterModifier,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let expand = |this: &mut Self,
+                      func: &mut MirFunction,
+                      block: u32,
+                      op: &str,
+                      pattern: &str,
+                      replacement: &str| {
+            let args = vec![
+                reg(&value),
+                MirValue::string(op),
+                MirValue::string(pattern),
+                MirValue::string(replacement),
+            ];
+            this.op(func, block, |dest| MirInstruction::SystemCall {
+                dest,
+                syscall_name: "expand".to_string(),
+                args,
+            })
+        };
+        match modifier {
+            ParameterModifier::Length => {
+                let string = reg(&value);
+                self.op(func, *current, |dest| MirInstruction::StringLength {
+                    dest,
+                    string,
+                })
+            }
+            ParameterModifier::UseDefault(_)
+            | ParameterModifier::AssignDefault(_)
+            | ParameterModifier::UseAlternative(_)
+            | ParameterModifier::ErrorIfUnset(_) => {
+                // 未設定と空文字列を同じに扱う (`:-` などのコロン付きの形)
+                let text = reg(&self.stringify(&value, func, *current));
+                let empty = self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left: text,
+                    right: MirValue::string(""),
+                });
+                let result = self.op(func, *current, |dest| MirInstruction::Move {
+                    dest,
+                    src: value.clone(),
+                });
+                let (then_block, join) = (func.create_block(), func.create_block());
+                match modifier {
+                    ParameterModifier::UseAlternative(word) => {
+                        branch(func, *current, empty, join, then_block);
+                        emit(
+                            func,
+                            then_block,
+                            MirInstruction::LoadImmediate {
+                                dest: result.clone(),
+                                value: MirValue::string(*word),
+                            },
+                        );
+                        jump(func, then_block, join);
+                    }
+                    ParameterModifier::ErrorIfUnset(message) => {
+                        branch(func, *current, empty, then_block, join);
+                        let message = match message {
+                            Some(m) => format!("{name}: {m}"),
+                            None => format!("{name}: parameter null or not set"),
+                        };
+                        emit(
+                            func,
+                            then_block,
+                            MirInstruction::Throw {
+                                value: MirValue::string(message),
+                            },
+                        );
+                    }
+                    ParameterModifier::UseDefault(word)
+                    | ParameterModifier::AssignDefault(word) => {
+                        branch(func, *current, empty, then_block, join);
+                        emit(
+                            func,
+                            then_block,
+                            MirInstruction::LoadImmediate {
+                                dest: result.clone(),
+                                value: MirValue::string(*word),
+                            },
+                        );
+                        if matches!(modifier, ParameterModifier::AssignDefault(_)) {
+                            self.assign(name, result.clone(), false, func, then_block);
+                        }
+                        jump(func, then_block, join);
+                    }
+                    _ => unreachable!(),
+                }
+                *current = join;
+                result
+            }
+            ParameterModifier::Substring { start, length } => {
+                let was_arith = std::mem::replace(&mut self.in_arith, true);
+                let start = reg(&self.lower_value(start, prog, func, current));
+                let length = length
+                    .as_ref()
+                    .map(|l| reg(&self.lower_value(l, prog, func, current)));
+                self.in_arith = was_arith;
+                let string = reg(&value);
+                self.op(func, *current, |dest| MirInstruction::Substring {
+                    dest,
+                    string,
+                    start,
+                    length,
+                })
+            }
+            ParameterModifier::RemoveSmallestPrefix(p) => expand(self, func, *current, "#", p, ""),
+            ParameterModifier::RemoveLargestPrefix(p) => expand(self, func, *current, "##", p, ""),
+            ParameterModifier::RemoveSmallestSuffix(p) => expand(self, func, *current, "%", p, ""),
+            ParameterModifier::RemoveLargestSuffix(p) => expand(self, func, *current, "%%", p, ""),
+            ParameterModifier::ReplaceFirst {
+                pattern,
+                replacement,
+            } => expand(
+                self,
+                func,
+                *current,
+                "/",
+                pattern,
+                replacement.unwrap_or(""),
+            ),
+            ParameterModifier::ReplaceAll {
+                pattern,
+                replacement,
+            } => expand(
+                self,
+                func,
+                *current,
+                "//",
+                pattern,
+                replacement.unwrap_or(""),
+            ),
+            ParameterModifier::UppercaseFirst(p) => expand(self, func, *current, "^", p, ""),
+            ParameterModifier::UppercaseAll(p) => expand(self, func, *current, "^^", p, ""),
+            ParameterModifier::LowercaseFirst(p) => expand(self, func, *current, ",", p, ""),
+            ParameterModifier::LowercaseAll(p) => expand(self, func, *current, ",,", p, ""),
+        }
     }
-}
-impl Lowerer {
-    fn fresh_reg(&mut self) -> MirRegister {
-        let id = self.reg_counter;
-        self.reg_counter += 1;
-        MirRegister::new(id)
+
+    fn lower_test_binary(
+        &mut self,
+        left: &AstNode,
+        operator: &TestOperator,
+        right: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        use TestOperator::*;
+        let l = self.lower_arg(left, prog, func, current);
+        let r = self.lower_arg(right, prog, func, current);
+        match operator {
+            NumericEqual | NumericNotEqual | NumericLess | NumericLessEqual | NumericGreater
+            | NumericGreaterEqual => {
+                // 数値として比較する (数字の文字列は整数に読み替えられる)
+                let number = |this: &mut Self, func: &mut MirFunction, v: &MirRegister| {
+                    let left = reg(v);
+                    reg(&this.op(func, *current, |dest| MirInstruction::Add {
+                        dest,
+                        left,
+                        right: MirValue::Integer(0),
+                    }))
+                };
+                let (left, right) = (number(self, func, &l), number(self, func, &r));
+                self.op(func, *current, |dest| match operator {
+                    NumericEqual => MirInstruction::Equal { dest, left, right },
+                    NumericNotEqual => MirInstruction::NotEqual { dest, left, right },
+                    NumericLess => MirInstruction::LessThan { dest, left, right },
+                    NumericLessEqual => MirInstruction::LessEqual { dest, left, right },
+                    NumericGreater => MirInstruction::GreaterThan { dest, left, right },
+                    _ => MirInstruction::GreaterEqual { dest, left, right },
+                })
+            }
+            FileNewer | FileOlder | FileSame => {
+                let flag = match operator {
+                    FileNewer => "-nt",
+                    FileOlder => "-ot",
+                    _ => "-ef",
+                };
+                let args = vec![reg(&l), MirValue::string(flag), reg(&r)];
+                self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "test".to_string(),
+                    args,
+                })
+            }
+            _ => {
+                let left = reg(&self.stringify(&l, func, *current));
+                let right = reg(&self.stringify(&r, func, *current));
+                self.op(func, *current, |dest| match operator {
+                    StringEqual => MirInstruction::Equal { dest, left, right },
+                    StringNotEqual => MirInstruction::NotEqual { dest, left, right },
+                    StringLess => MirInstruction::Compare {
+                        dest,
+                        left,
+                        right,
+                        op: "lt".to_string(),
+                    },
+                    StringGreater => MirInstruction::Compare {
+                        dest,
+                        left,
+                        right,
+                        op: "gt".to_string(),
+                    },
+                    _ => MirInstruction::RegexMatch {
+                        dest,
+                        value: left,
+                        pattern: right,
+                        not: *operator == StringNotMatch,
+                    },
+                })
+            }
+        }
     }
 
-    pub fn lower_program(mut self, ast: &AstNode) -> MirProgram {
-        let mut prog = MirProgram::new();
-        let mut func = MirFunction::new("main".to_string(), Vec::new());
-        let entry = func.entry_block;
-        self.lower_node_prog(ast, &mut prog, &mut func, entry);
-        if let Some(b) = func.get_block_mut(entry) {
-            if !matches!(b.instructions.last(), Some(MirInstruction::Return { .. })) {
-                b.instructions.push(MirInstruction::Return {
-                    value: Some(MirValue::Null),
+    fn lower_test_unary(
+        &mut self,
+        operator: &TestUnaryOperator,
+        operand: &AstNode,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        match operator {
+            TestUnaryOperator::VariableSet | TestUnaryOperator::VariableArray => {
+                // 被演算子は変数名
+                let value = match operand {
+                    AstNode::Word(name)
+                    | AstNode::Variable(name)
+                    | AstNode::StringLiteral { value: name, .. }
+                    | AstNode::VariableExpansion { name, .. } => {
+                        self.read_var(name, func, *current)
+                    }
+                    other => self.lower_value(other, prog, func, current),
+                };
+                if *operator == TestUnaryOperator::VariableSet {
+                    let left = reg(&value);
+                    return self.op(func, *current, |dest| MirInstruction::NotEqual {
+                        dest,
+                        left,
+                        right: MirValue::Null,
+                    });
+                }
+                let args = vec![reg(&value)];
+                let actual = self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "typeof".to_string(),
+                    args,
                 });
+                self.op(func, *current, |dest| MirInstruction::Equal {
+                    dest,
+                    left: MirValue::Register(actual),
+                    right: MirValue::string("array"),
+                })
+            }
+            TestUnaryOperator::StringEmpty | TestUnaryOperator::StringNonEmpty => {
+                let value = self.lower_arg(operand, prog, func, current);
+                let left = reg(&self.stringify(&value, func, *current));
+                let right = MirValue::string("");
+                self.op(func, *current, |dest| {
+                    if *operator == TestUnaryOperator::StringEmpty {
+                        MirInstruction::Equal { dest, left, right }
+                    } else {
+                        MirInstruction::NotEqual { dest, left, right }
+                    }
+                })
+            }
+            file_test => {
+                let value = self.lower_arg(operand, prog, func, current);
+                let args = vec![MirValue::string(test_flag(file_test)), reg(&value)];
+                self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "test".to_string(),
+                    args,
+                })
             }
         }
-        prog.add_function(func);
-        prog
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn lower_assignment(
+        &mut self,
+        name: &str,
+        operator: &AssignmentOperator,
+        value: &AstNode,
+        is_local: bool,
+        prog: &mut MirProgram,
+        func: &mut MirFunction,
+        current: &mut u32,
+    ) -> MirRegister {
+        let v = self.lower_value(value, prog, func, current);
+        let value = if *operator == AssignmentOperator::Assign {
+            v
+        } else {
+            let old = reg(&self.read_var(name, func, *current));
+            let new = reg(&v);
+            self.op(func, *current, |dest| match operator {
+                // シェルの += は文字列の連結
+                AssignmentOperator::AddAssign | AssignmentOperator::Append => {
+                    MirInstruction::Concat {
+                        dest,
+                        parts: vec![old, new],
+                    }
+                }
+                AssignmentOperator::Prepend => MirInstruction::Concat {
+                    dest,
+                    parts: vec![new, old],
+                },
+                AssignmentOperator::SubAssign => MirInstruction::Sub {
+                    dest,
+                    left: old,
+                    right: new,
+                },
+                AssignmentOperator::MulAssign => MirInstruction::Mul {
+                    dest,
+                    left: old,
+                    right: new,
+                },
+                AssignmentOperator::DivAssign => MirInstruction::Div {
+                    dest,
+                    left: old,
+                    right: new,
+                },
+                _ => MirInstruction::Mod {
+                    dest,
+                    left: old,
+                    right: new,
+                },
+            })
+        };
+        self.assign(name, value.clone(), is_local, func, *current);
+        value
     }
 
     fn lower_node_prog(
@@ -54,43 +1871,66 @@ impl Lowerer {
         node: &AstNode,
         prog: &mut MirProgram,
         func: &mut MirFunction,
-        current_block: u32,
+        current: &mut u32,
     ) -> Option<MirRegister> {
         match node {
             AstNode::Program(stmts) | AstNode::StatementList(stmts) => {
-                let mut last_reg: Option<MirRegister> = None;
-                for s in stmts {
-                    if let Some(r) = self.lower_node_prog(s, prog, func, current_block) {
-                        last_reg = Some(r);
-                    }
-                    // 途中で明示的 Return/ClosureReturn が出たら以降は無視 (既にブロック末端確定)
-                    if let Some(block) = func.get_block(current_block) {
-                        if matches!(
-                            block.instructions.last(),
-                            Some(MirInstruction::Return { .. })
-                                | Some(MirInstruction::ClosureReturn { .. })
-                        ) {
-                            return None;
-                        }
-                    }
+                self.lower_seq(stmts, prog, func, current)
+            }
+            AstNode::Sequence { left, right } => {
+                let first = self.lower_node_prog(left, prog, func, current);
+                if is_terminated(func, *current) {
+                    return first;
                 }
-                if let Some(block) = func.get_block_mut(current_block) {
-                    if !matches!(
-                        block.instructions.last(),
-                        Some(MirInstruction::Return { .. })
-                            | Some(MirInstruction::ClosureReturn { .. })
-                    ) {
-                        if let Some(r) = last_reg {
-                            block.instructions.push(MirInstruction::Return {
-                                value: Some(MirValue::Register(r)),
-                            });
-                        } else {
-                            block.instructions.push(MirInstruction::Return {
-                                value: Some(MirValue::Null),
-                            });
-                        }
+                self.lower_node_prog(right, prog, func, current).or(first)
+            }
+            AstNode::CompoundCommand(inner)
+            | AstNode::BraceGroup(inner)
+            | AstNode::AsyncBlock(inner)
+            | AstNode::AwaitExpression(inner) => self.lower_node_prog(inner, prog, func, current),
+            AstNode::Subshell(inner) => {
+                self.scopes.push(Scope::new(ScopeKind::Subshell));
+                let value = self.lower_node_prog(inner, prog, func, current);
+                self.scopes.pop();
+                value
+            }
+            AstNode::Background(inner) => Some(self.lower_background(inner, prog, func, current)),
+            AstNode::JobGroup(members) => {
+                // Members of `a &&& b` run here one after another, each in a
+                // subshell scope; the group ends with the rightmost non-zero
+                // status
+                let result = self.load(func, *current, MirValue::Integer(0));
+                for member in members {
+                    self.scopes.push(Scope::new(ScopeKind::Subshell));
+                    let value = self.lower_value(member, prog, func, current);
+                    self.scopes.pop();
+                    if is_terminated(func, *current) {
+                        break;
                     }
+                    let ok = self.succeeded(&value, func, *current);
+                    let (failed, join) = (func.create_block(), func.create_block());
+                    branch(func, *current, ok, join, failed);
+                    let status = self.op(func, failed, |dest| MirInstruction::Status {
+                        dest,
+                        value: reg(&value),
+                    });
+                    emit(
+                        func,
+                        failed,
+                        MirInstruction::Move {
+                            dest: result.clone(),
+                            src: status,
+                        },
+                    );
+                    jump(func, failed, join);
+                    *current = join;
                 }
+                // `$?` is the group's status, not its last member's
+                self.assign("?", result.clone(), false, func, *current);
+                Some(result)
+            }
+            AstNode::ModuleDeclaration { body, .. } => {
+                self.lower_node_prog(body, prog, func, current);
                 None
             }
             AstNode::Function {
@@ -99,125 +1939,204 @@ impl Lowerer {
             | AstNode::FunctionDeclaration {
                 name, params, body, ..
             } => {
-                // 別関数として MirProgram に登録し、本体を独立に lowering する
-                let param_names: Vec<String> = params.iter().map(|p| p.name.to_string()).collect();
-                let mut f = MirFunction::new((*name).to_string(), param_names);
-                let entry_block = f.entry_block;
-                // ネスト関数は独立した Lowerer で環境をリセットして lower する
-                let mut nested = Lowerer::new();
-                nested.lower_node_prog(body, prog, &mut f, entry_block);
-                if let Some(bblk) = f.get_block_mut(entry_block) {
-                    if !matches!(
-                        bblk.instructions.last(),
-                        Some(MirInstruction::Return { .. })
-                            | Some(MirInstruction::ClosureReturn { .. })
-                    ) {
-                        bblk.instructions.push(MirInstruction::Return {
-                            value: Some(MirValue::Null),
-                        });
-                    }
-                }
-                prog.add_function(f);
+                self.lower_function(name, params, body, prog);
                 None
             }
             AstNode::FunctionCall { name, args, .. } => {
-                // Callee を lower。Word で var_env に登録済みなら closure 変数呼び出し、それ以外の Word は通常関数。
-                let func_reg = self.lower_node_prog(name, prog, func, current_block);
+                // Word ならローカル変数・代入済みグローバルのクロージャ、それ以外は関数名。
+                // 式なら lower した値をクロージャとして呼ぶ
+                let callee = match &**name {
+                    AstNode::Word(word) => match self.lookup(word) {
+                        Some(r) => Some(r),
+                        None if self.globals.contains(*word) => {
+                            Some(self.read_var(word, func, *current))
+                        }
+                        None => None,
+                    },
+                    other => Some(self.lower_value(other, prog, func, current)),
+                };
                 let mut arg_vals = Vec::new();
                 for a in args {
-                    if let Some(r) = self.lower_node_prog(a, prog, func, current_block) {
-                        arg_vals.push(MirValue::Register(r));
-                    }
+                    arg_vals.push(reg(&self.lower_value(a, prog, func, current)));
                 }
-                let dest = self.fresh_reg();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    match &**name {
-                        AstNode::Word(w) => {
-                            if let Some(reg) = self.var_env.get(*w) {
-                                // 変数に格納されたクロージャ (または動的 callable) を ClosureCall
-                                block.instructions.push(MirInstruction::ClosureCall {
-                                    dest: dest.clone(),
-                                    closure: MirValue::Register(reg.clone()),
-                                    args: arg_vals,
-                                });
-                            } else {
-                                // 通常の関数名呼び出し
-                                block.instructions.push(MirInstruction::Call {
-                                    dest: dest.clone(),
-                                    function: w.to_string(),
-                                    args: arg_vals,
-                                });
-                            }
-                        }
-                        _ => {
-                            // 動的: lower された register を closure として呼ぶ
-                            if let Some(r) = func_reg {
-                                block.instructions.push(MirInstruction::ClosureCall {
-                                    dest: dest.clone(),
-                                    closure: MirValue::Register(r),
-                                    args: arg_vals,
-                                });
-                            } else {
-                                block.instructions.push(MirInstruction::LoadImmediate {
-                                    dest: dest.clone(),
-                                    value: MirValue::Null,
-                                });
-                            }
-                        }
+                Some(match (callee, &**name) {
+                    (Some(closure), _) => {
+                        self.op(func, *current, |dest| MirInstruction::ClosureCall {
+                            dest,
+                            closure: MirValue::Register(closure),
+                            args: arg_vals,
+                        })
                     }
-                }
-                Some(dest)
+                    (None, name) => {
+                        let function = match name {
+                            AstNode::Word(word) => word.to_string(),
+                            _ => String::new(),
+                        };
+                        self.op(func, *current, |dest| MirInstruction::Call {
+                            dest,
+                            function,
+                            args: arg_vals,
+                        })
+                    }
+                })
             }
-            AstNode::NumberLiteral { value, .. } => {
-                if let Ok(n) = value.parse::<i64>() {
-                    let r = self.fresh_reg();
-                    if let Some(block) = func.get_block_mut(current_block) {
-                        block.instructions.push(MirInstruction::LoadImmediate {
-                            dest: r.clone(),
-                            value: MirValue::Integer(n),
-                        });
+            AstNode::NumberLiteral { value, number_type } => {
+                use nxsh_parser::ast::NumberType;
+                let digits = value.replace('_', "");
+                let parsed = match number_type {
+                    NumberType::Decimal => digits.parse::<i64>().ok().map(MirValue::Integer),
+                    NumberType::Hexadecimal => {
+                        let hex = digits.trim_start_matches("0x").trim_start_matches("0X");
+                        i64::from_str_radix(hex, 16).ok().map(MirValue::Integer)
                     }
-                    Some(r)
-                } else {
-                    None
-                }
+                    NumberType::Octal => {
+                        let octal = digits.trim_start_matches("0o").trim_start_matches("0O");
+                        i64::from_str_radix(octal, 8).ok().map(MirValue::Integer)
+                    }
+                    NumberType::Binary => {
+                        let binary = digits.trim_start_matches("0b").trim_start_matches("0B");
+                        i64::from_str_radix(binary, 2).ok().map(MirValue::Integer)
+                    }
+                    NumberType::Float => digits.parse::<f64>().ok().map(MirValue::Float),
+                };
+                let value = parsed.unwrap_or_else(|| MirValue::string(*value));
+                Some(self.load(func, *current, value))
             }
             AstNode::StringLiteral { value, .. } => {
-                let r = self.fresh_reg();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block.instructions.push(MirInstruction::LoadImmediate {
-                        dest: r.clone(),
-                        value: MirValue::String((*value).to_string()),
-                    });
-                }
-                Some(r)
+                let value = self.intern(value);
+                Some(self.load(func, *current, value))
             }
             AstNode::Word(value) => {
                 // 既知変数(レジスタ)ならロード不要で再利用
-                if let Some(reg) = self.var_env.get(*value) {
-                    return Some(reg.clone());
-                }
-                let r = self.fresh_reg();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block.instructions.push(MirInstruction::LoadImmediate {
-                        dest: r.clone(),
-                        value: MirValue::String((*value).to_string()),
-                    });
+                if let Some(r) = self.lookup(value) {
+                    return Some(r);
+                }
+                if self.in_arith {
+                    if let Ok(n) = value.parse::<i64>() {
+                        return Some(self.load(func, *current, MirValue::Integer(n)));
+                    }
+                }
+                if (self.in_arith && is_name(value)) || self.globals.contains(*value) {
+                    return Some(self.read_var(value, func, *current));
+                }
+                let value = self.intern(value);
+                Some(self.load(func, *current, value))
+            }
+            AstNode::Variable(name) => Some(self.read_var(name, func, *current)),
+            AstNode::VariableExpansion { name, modifier } => {
+                // `$err.message`: オブジェクトならフィールドを辿る
+                if let (None, Some((base, fields))) = (modifier, name.split_once('.')) {
+                    if is_name(base) {
+                        let mut value = self.read_var(base, func, *current);
+                        for field in fields.split('.') {
+                            let args = vec![reg(&value), self.intern(field)];
+                            value = self.op(func, *current, |dest| MirInstruction::SystemCall {
+                                dest,
+                                syscall_name: "member".to_string(),
+                                args,
+                            });
+                        }
+                        return Some(value);
+                    }
+                }
+                let value = self.read_var(name, func, *current);
+                Some(match modifier {
+                    Some(modifier) => {
+                        self.lower_modifier(name, value, modifier, prog, func, current)
+                    }
+                    None => value,
+                })
+            }
+            AstNode::CommandSubstitution { command, .. }
+            | AstNode::ProcessSubstitution { command, .. } => {
+                let block = self.lower_detached(command, prog, func);
+                Some(self.op(func, *current, |dest| MirInstruction::Capture {
+                    dest,
+                    block,
+                }))
+            }
+            AstNode::ArithmeticExpansion { expr, .. } => {
+                let was_arith = std::mem::replace(&mut self.in_arith, true);
+                let value = self.lower_value(expr, prog, func, current);
+                self.in_arith = was_arith;
+                Some(value)
+            }
+            AstNode::PathnameExpansion { pattern } => {
+                let args = vec![
+                    MirValue::string(glob_pattern_text(pattern)),
+                    MirValue::string(glob_pattern_regex(pattern)),
+                ];
+                Some(self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "glob".to_string(),
+                    args,
+                }))
+            }
+            AstNode::BraceExpansion { elements } => {
+                let elements = expand_braces(elements)
+                    .into_iter()
+                    .map(MirValue::string)
+                    .collect();
+                Some(self.op(func, *current, |dest| MirInstruction::MakeArray {
+                    dest,
+                    elements,
+                }))
+            }
+            AstNode::TildeExpansion { user } => Some(match user {
+                None => self.read_var("HOME", func, *current),
+                Some(user) => self.load(func, *current, MirValue::string(format!("~{user}"))),
+            }),
+            AstNode::Array(items) | AstNode::ArgumentList(items) => {
+                let mut elements = Vec::new();
+                for item in items {
+                    elements.push(reg(&self.lower_value(item, prog, func, current)));
                 }
-                Some(r)
+                Some(self.op(func, *current, |dest| MirInstruction::MakeArray {
+                    dest,
+                    elements,
+                }))
             }
             AstNode::Try {
                 body,
                 catch_clauses,
                 finally_clause,
-            } => {
-                self.lower_node_prog(body, prog, func, current_block);
-                for clause in catch_clauses {
-                    self.lower_node_prog(&clause.body, prog, func, current_block);
-                }
-                if let Some(fin) = finally_clause {
-                    self.lower_node_prog(fin, prog, func, current_block);
-                }
+            } => Some(self.lower_try(
+                body,
+                catch_clauses,
+                finally_clause.as_deref(),
+                prog,
+                func,
+                current,
+            )),
+            AstNode::ThrowStatement(value) => {
+                let value = match &**value {
+                    // `throw message code`: 終了ステータス付きのエラーオブジェクト
+                    AstNode::ArgumentList(parts) if parts.len() == 2 => {
+                        let message = reg(&self.lower_arg(&parts[0], prog, func, current));
+                        let code = reg(&self.lower_arg(&parts[1], prog, func, current));
+                        let fields = vec![
+                            ("__type__".to_string(), self.intern("error")),
+                            ("message".to_string(), message),
+                            ("code".to_string(), code),
+                        ];
+                        self.op(func, *current, |dest| MirInstruction::MakeObject {
+                            dest,
+                            fields,
+                        })
+                    }
+                    value => self.lower_arg(value, prog, func, current),
+                };
+                emit(func, *current, MirInstruction::Throw { value: reg(&value) });
+                None
+            }
+            AstNode::Error { message, .. } => {
+                emit(
+                    func,
+                    *current,
+                    MirInstruction::Throw {
+                        value: MirValue::string(message.as_str()),
+                    },
+                );
                 None
             }
             AstNode::Closure {
@@ -225,124 +2144,463 @@ impl Lowerer {
                 captures,
                 params,
                 ..
+            } => Some(self.lower_closure(params, body, captures, prog, func, current)),
+            AstNode::VariableAssignment {
+                name,
+                operator,
+                value,
+                is_local,
+                ..
+            }
+            | AstNode::Assignment {
+                name,
+                operator,
+                value,
+                is_local,
+                ..
+            } => Some(self.lower_assignment(name, operator, value, *is_local, prog, func, current)),
+            AstNode::ArrayAssignment {
+                name,
+                operator,
+                elements,
+                is_local,
+                ..
             } => {
-                let body_block = func.create_block();
-                // 既存環境保存 (ネスト用)
-                let saved_env = self.var_env.clone();
-                let was_in_closure = self.in_closure;
-                self.in_closure = true;
-                // パラメータレジスタ割当て & 環境反映
-                let mut param_regs = Vec::new();
-                let mut param_names = Vec::new();
-                for p in params {
-                    let r = self.fresh_reg();
-                    self.var_env.insert(p.name.to_string(), r.clone());
-                    param_regs.push(r);
-                    param_names.push(p.name.to_string());
-                }
-                // capture_regs: body 内で参照するローカルコピー (実行時に ClosureCall で値注入)
-                let mut capture_regs = Vec::new();
-                for cap in captures {
-                    // 外側 (saved_env) で見つかったらそのレジスタを capture 値として使用。body 内では新レジスタを割当。
-                    let new_reg = self.fresh_reg();
-                    self.var_env.insert((*cap).to_string(), new_reg.clone());
-                    capture_regs.push(new_reg);
-                }
-                // body lowering（クロージャ内であることを示すフラグのもとで）
-                self.lower_node_prog(body, prog, func, body_block);
-                if let Some(bblk) = func.get_block_mut(body_block) {
-                    if !matches!(
-                        bblk.instructions.last(),
-                        Some(MirInstruction::Return { .. })
-                            | Some(MirInstruction::ClosureReturn { .. })
-                    ) {
-                        bblk.instructions.push(MirInstruction::Return {
-                            value: Some(MirValue::Null),
-                        });
-                    }
-                }
-                let dest = self.fresh_reg();
-                // capture_vals: 外側の元レジスタがあればそれ、無ければ文字列識別子
-                let capture_vals: Vec<MirValue> = captures
-                    .iter()
-                    .map(|c| {
-                        if let Some(orig) = saved_env.get(*c) {
-                            MirValue::Register(orig.clone())
-                        } else {
-                            MirValue::String(c.to_string())
-                        }
+                let append = matches!(
+                    operator,
+                    AssignmentOperator::AddAssign | AssignmentOperator::Append
+                );
+                let array = if append {
+                    self.read_var(name, func, *current)
+                } else {
+                    self.op(func, *current, |dest| MirInstruction::MakeArray {
+                        dest,
+                        elements: Vec::new(),
                     })
-                    .collect();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block.instructions.push(MirInstruction::ClosureCreate {
-                        dest: dest.clone(),
-                        func_block: body_block,
-                        captures: capture_vals,
-                        capture_regs,
-                        param_regs,
-                        param_names,
-                    });
+                };
+                for element in elements {
+                    let value = reg(&self.lower_value(&element.value, prog, func, current));
+                    // 添字の無い要素は末尾に追加する
+                    let index = match &element.index {
+                        Some(index) => {
+                            let was_arith = std::mem::replace(&mut self.in_arith, true);
+                            let index = self.lower_value(index, prog, func, current);
+                            self.in_arith = was_arith;
+                            reg(&index)
+                        }
+                        None => {
+                            let array = reg(&array);
+                            reg(
+                                &self.op(func, *current, |dest| MirInstruction::ArrayLength {
+                                    dest,
+                                    array,
+                                }),
+                            )
+                        }
+                    };
+                    emit(
+                        func,
+                        *current,
+                        MirInstruction::ArraySet {
+                            array: reg(&array),
+                            index,
+                            value,
+                        },
+                    );
+                }
+                self.assign(name, array.clone(), *is_local, func, *current);
+                Some(array)
+            }
+            AstNode::DestructureAssignment {
+                pattern,
+                value,
+                is_local,
+            } => {
+                let value = self.lower_value(value, prog, func, current);
+                self.lower_pattern(pattern, &value, false, *is_local, prog, func, current);
+                Some(value)
+            }
+            AstNode::LetBinding { pattern, value, .. } => {
+                let value = self.lower_value(value, prog, func, current);
+                self.lower_pattern(pattern, &value, false, true, prog, func, current);
+                Some(value)
+            }
+            AstNode::MacroInvocation { name, .. } => {
+                let value = MirValue::string(format!("macro:{name}"));
+                Some(self.load(func, *current, value))
+            }
+            AstNode::Command {
+                name,
+                args,
+                redirections,
+                background,
+            } => {
+                if *background {
+                    let foreground = AstNode::Command {
+                        name: name.clone(),
+                        args: args.clone(),
+                        redirections: redirections.clone(),
+                        background: false,
+                    };
+                    return Some(self.lower_background(&foreground, prog, func, current));
                 }
-                // 環境復元 (クロージャスコープ脱出)
-                self.var_env = saved_env;
-                self.in_closure = was_in_closure;
-                Some(dest)
+                Some(self.lower_command(name, args, redirections, prog, func, current))
+            }
+            AstNode::SimpleCommand { name, args } => {
+                let command = name.to_string();
+                let args = args.iter().map(|a| self.intern(a)).collect();
+                Some(
+                    self.op(func, *current, |dest| MirInstruction::ExecuteCommand {
+                        dest,
+                        command,
+                        args,
+                    }),
+                )
             }
-            AstNode::VariableAssignment { name, value, .. } => {
-                // 代入値を lower し、環境へ登録 (値がレジスタならキャプチャ対象として利用可能)
-                if let Some(r) = self.lower_node_prog(value, prog, func, current_block) {
-                    self.var_env.insert(name.to_string(), r.clone());
-                    Some(r)
+            AstNode::Pipeline {
+                elements,
+                operators,
+            } => Some(self.lower_pipeline(elements, operators, prog, func, current)),
+            AstNode::LogicalAnd { left, right } | AstNode::LogicalOr { left, right } => {
+                // コマンドの && / || は左辺の終了ステータスで右辺を実行するか決める
+                let left_value = self.lower_value(left, prog, func, current);
+                let result = self.op(func, *current, |dest| MirInstruction::Move {
+                    dest,
+                    src: left_value,
+                });
+                let ok = self.succeeded(&result, func, *current);
+                let (mut rhs, join) = (func.create_block(), func.create_block());
+                if matches!(node, AstNode::LogicalAnd { .. }) {
+                    branch(func, *current, ok, rhs, join);
                 } else {
-                    None
+                    branch(func, *current, ok, join, rhs);
                 }
+                self.lower_arm(right, &result, join, prog, func, &mut rhs);
+                *current = join;
+                Some(result)
             }
-            AstNode::MacroInvocation { name, .. } => {
-                let reg = self.fresh_reg();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block.instructions.push(MirInstruction::LoadImmediate {
-                        dest: reg.clone(),
-                        value: MirValue::String(format!("macro:{name}")),
-                    });
+            AstNode::Return(expr) => {
+                // 先に式を lower (これで current へ追加) し終えてから Return を置く
+                let val = match expr {
+                    Some(e) => reg(&self.lower_value(e, prog, func, current)),
+                    None => MirValue::Null,
+                };
+                for _ in 0..self.try_depth {
+                    emit(func, *current, MirInstruction::TryEnd);
                 }
-                Some(reg)
+                emit(func, *current, MirInstruction::Return { value: Some(val) });
+                None
+            }
+            AstNode::Exit(code) => {
+                // 引数が無ければ直前のコマンドの終了ステータスで終わる
+                let code = match code {
+                    Some(code) => reg(&self.lower_value(code, prog, func, current)),
+                    None => reg(&self.read_var("?", func, *current)),
+                };
+                self.op(func, *current, |dest| MirInstruction::SystemCall {
+                    dest,
+                    syscall_name: "exit".to_string(),
+                    args: vec![code],
+                });
+                emit(func, *current, MirInstruction::Unreachable);
+                None
             }
-            AstNode::Command { name, args, .. } => {
-                let mut parts = Vec::new();
-                if let AstNode::Word(w) = &**name {
-                    parts.push(w.to_string());
+            AstNode::Break(label) | AstNode::Continue(label) => {
+                // `break 2` のような数値は抜けるループの数
+                let levels = label
+                    .and_then(|l| l.parse::<usize>().ok())
+                    .unwrap_or(1)
+                    .max(1);
+                let index = self.loops.len().checked_sub(levels.min(self.loops.len()))?;
+                let target = self.loops.get(index)?;
+                let (block, depth) = match node {
+                    AstNode::Break(_) => (target.break_block, target.try_depth),
+                    _ => (target.continue_block, target.try_depth),
+                };
+                for _ in depth..self.try_depth {
+                    emit(func, *current, MirInstruction::TryEnd);
                 }
-                for a in args {
-                    if let AstNode::Word(w) = a {
-                        parts.push(w.to_string());
-                    }
+                jump(func, *current, block);
+                None
+            }
+            AstNode::If {
+                condition,
+                then_branch,
+                elif_branches,
+                else_branch,
+            } => {
+                let result = self.load(func, *current, MirValue::Null);
+                let join = func.create_block();
+                let arms = std::iter::once((&**condition, &**then_branch))
+                    .chain(elif_branches.iter().map(|arm| (&arm.0, &arm.1)));
+                for (cond, body) in arms {
+                    let ok = self.lower_condition(cond, prog, func, current);
+                    let (mut then_block, next) = (func.create_block(), func.create_block());
+                    branch(func, *current, ok, then_block, next);
+                    self.lower_arm(body, &result, join, prog, func, &mut then_block);
+                    *current = next;
                 }
-                let r = self.fresh_reg();
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block.instructions.push(MirInstruction::ExecuteCommand {
-                        dest: r.clone(),
-                        command: parts.first().cloned().unwrap_or_default(),
-                        args: Vec::new(),
-                    });
+                match else_branch {
+                    Some(body) => self.lower_arm(body, &result, join, prog, func, current),
+                    None => jump(func, *current, join),
                 }
-                Some(r)
+                *current = join;
+                Some(result)
             }
-            AstNode::Return(expr) => {
-                // 先に式を lower (これで current_block へ追加) し終えてから、再度 block を取り直す
-                let val = if let Some(e) = expr {
-                    self.lower_node_prog(e, prog, func, current_block)
-                        .map(MirValue::Register)
-                        .unwrap_or(MirValue::Null)
+            AstNode::ConditionalExpression {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                let condition = self.lower_value(condition, prog, func, current);
+                let ok = self.truthy(&condition, func, *current);
+                let result = self.load(func, *current, MirValue::Null);
+                let join = func.create_block();
+                let (mut then_block, mut else_block) = (func.create_block(), func.create_block());
+                branch(func, *current, ok, then_block, else_block);
+                self.lower_arm(then_expr, &result, join, prog, func, &mut then_block);
+                self.lower_arm(else_expr, &result, join, prog, func, &mut else_block);
+                *current = join;
+                Some(result)
+            }
+            AstNode::While { condition, body } | AstNode::Until { condition, body } => {
+                let (header, body_block, exit) = (
+                    func.create_block(),
+                    func.create_block(),
+                    func.create_block(),
+                );
+                jump(func, *current, header);
+                *current = header;
+                let ok = self.lower_condition(condition, prog, func, current);
+                if matches!(node, AstNode::While { .. }) {
+                    branch(func, *current, ok, body_block, exit);
                 } else {
-                    MirValue::Null
-                };
-                if let Some(block) = func.get_block_mut(current_block) {
-                    block
-                        .instructions
-                        .push(MirInstruction::Return { value: Some(val) });
+                    branch(func, *current, ok, exit, body_block);
+                }
+                self.lower_loop_body(body, body_block, header, exit, prog, func);
+                *current = exit;
+                None
+            }
+            AstNode::ForC {
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                let was_arith = std::mem::replace(&mut self.in_arith, true);
+                if let Some(init) = init {
+                    self.lower_node_prog(init, prog, func, current);
+                }
+                let (header, body_block, step, exit) = (
+                    func.create_block(),
+                    func.create_block(),
+                    func.create_block(),
+                    func.create_block(),
+                );
+                jump(func, *current, header);
+                *current = header;
+                match condition {
+                    Some(condition) => {
+                        let value = self.lower_value(condition, prog, func, current);
+                        let ok = self.truthy(&value, func, *current);
+                        branch(func, *current, ok, body_block, exit);
+                    }
+                    None => jump(func, *current, body_block),
                 }
+                self.in_arith = was_arith;
+                self.lower_loop_body(body, body_block, step, exit, prog, func);
+                let mut step_block = step;
+                self.in_arith = true;
+                if let Some(update) = update {
+                    self.lower_node_prog(update, prog, func, &mut step_block);
+                }
+                self.in_arith = was_arith;
+                jump(func, step_block, header);
+                *current = exit;
+                None
+            }
+            AstNode::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                let items = reg(&self.lower_value(iterable, prog, func, current));
+                let iterator = self.op(func, *current, |dest| MirInstruction::GetIterator {
+                    dest,
+                    iterable: items,
+                });
+                let (header, body_block, exit) = (
+                    func.create_block(),
+                    func.create_block(),
+                    func.create_block(),
+                );
+                jump(func, *current, header);
+                let (element, has_next) = (self.fresh_reg(), self.fresh_reg());
+                emit(
+                    func,
+                    header,
+                    MirInstruction::IteratorNext {
+                        iterator: MirValue::Register(iterator),
+                        element: element.clone(),
+                        has_next: has_next.clone(),
+                    },
+                );
+                branch(func, header, has_next, body_block, exit);
+                self.assign(variable, element, false, func, body_block);
+                self.lower_loop_body(body, body_block, header, exit, prog, func);
+                *current = exit;
+                None
+            }
+            AstNode::Select {
+                variable,
+                options,
+                body,
+            } => {
+                let options = match options {
+                    Some(options) => self.lower_value(options, prog, func, current),
+                    None => self.read_var("@", func, *current),
+                };
+                let (header, body_block, exit) = (
+                    func.create_block(),
+                    func.create_block(),
+                    func.create_block(),
+                );
+                jump(func, *current, header);
+                // 選ばれた項目が空 (入力の終わり) ならループを抜ける
+                let choice = self.op(func, header, |dest| MirInstruction::ExecuteCommand {
+                    dest,
+                    command: "select".to_string(),
+                    args: vec![MirValue::Register(options)],
+                });
+                let ok = self.truthy(&choice, func, header);
+                branch(func, header, ok, body_block, exit);
+                self.assign(variable, choice, false, func, body_block);
+                self.lower_loop_body(body, body_block, header, exit, prog, func);
+                *current = exit;
                 None
             }
+            AstNode::Case { expr, arms } => {
+                let value = self.lower_value(expr, prog, func, current);
+                let subject = self.stringify(&value, func, *current);
+                let result = self.load(func, *current, MirValue::Null);
+                let join = func.create_block();
+                for arm in arms {
+                    let mut body = func.create_block();
+                    for pattern in &arm.patterns {
+                        match self
+                            .lower_pattern(pattern, &subject, true, false, prog, func, current)
+                        {
+                            Some(ok) => {
+                                let next = func.create_block();
+                                branch(func, *current, ok, body, next);
+                                *current = next;
+                            }
+                            None => {
+                                // `*` より後ろの腕には到達しない
+                                jump(func, *current, body);
+                                *current = func.create_block();
+                                break;
+                            }
+                        }
+                    }
+                    self.lower_arm(&arm.body, &result, join, prog, func, &mut body);
+                }
+                jump(func, *current, join);
+                *current = join;
+                Some(result)
+            }
+            AstNode::Match { expr, arms, .. } => {
+                Some(self.lower_match(expr, arms, None, prog, func, current))
+            }
+            AstNode::MatchExpression {
+                expr,
+                arms,
+                default_arm,
+            } => Some(self.lower_match(expr, arms, default_arm.as_deref(), prog, func, current)),
+            AstNode::TestExpression { condition, .. } => match &**condition {
+                AstNode::TestBinary { .. }
+                | AstNode::TestUnary { .. }
+                | AstNode::TestExpression { .. }
+                | AstNode::BinaryExpression { .. }
+                | AstNode::UnaryExpression { .. }
+                | AstNode::LogicalAnd { .. }
+                | AstNode::LogicalOr { .. } => self.lower_node_prog(condition, prog, func, current),
+                // `[ word ]` は空文字列でなければ真
+                operand => Some(self.lower_test_unary(
+                    &TestUnaryOperator::StringNonEmpty,
+                    operand,
+                    prog,
+                    func,
+                    current,
+                )),
+            },
+            AstNode::TestBinary {
+                left,
+                operator,
+                right,
+            } => Some(self.lower_test_binary(left, operator, right, prog, func, current)),
+            AstNode::TestUnary { operator, operand } => {
+                Some(self.lower_test_unary(operator, operand, prog, func, current))
+            }
+            AstNode::UnaryExpression { operator, operand } => {
+                let value = self.lower_value(operand, prog, func, current);
+                let operand = reg(&value);
+                Some(match operator {
+                    UnaryOperator::Plus => value,
+                    UnaryOperator::Minus => self.op(func, *current, |dest| MirInstruction::Sub {
+                        dest,
+                        left: MirValue::Integer(0),
+                        right: operand,
+                    }),
+                    UnaryOperator::LogicalNot => {
+                        self.op(func, *current, |dest| MirInstruction::Not { dest, operand })
+                    }
+                    UnaryOperator::BitwiseNot => {
+                        self.op(func, *current, |dest| MirInstruction::BitXor {
+                            dest,
+                            left: operand,
+                            right: MirValue::Integer(-1),
+                        })
+                    }
+                })
+            }
+            AstNode::PostfixExpression { operand, operator } => {
+                let delta = match operator {
+                    PostfixOperator::Increment => 1,
+                    PostfixOperator::Decrement => -1,
+                };
+                match &**operand {
+                    AstNode::Word(name)
+                    | AstNode::Variable(name)
+                    | AstNode::VariableExpansion {
+                        name,
+                        modifier: None,
+                    } => {
+                        // 値は更新前のもの
+                        let old = self.read_var(name, func, *current);
+                        let before = self.op(func, *current, |dest| MirInstruction::Move {
+                            dest,
+                            src: old.clone(),
+                        });
+                        let new = self.op(func, *current, |dest| MirInstruction::Add {
+                            dest,
+                            left: MirValue::Register(old),
+                            right: MirValue::Integer(delta),
+                        });
+                        self.assign(name, new, false, func, *current);
+                        Some(before)
+                    }
+                    other => {
+                        let left = reg(&self.lower_value(other, prog, func, current));
+                        Some(self.op(func, *current, |dest| MirInstruction::Add {
+                            dest,
+                            left,
+                            right: MirValue::Integer(delta),
+                        }))
+                    }
+                }
+            }
             AstNode::BinaryExpression {
                 left,
                 operator,
@@ -350,222 +2608,211 @@ impl Lowerer {
             } => {
                 use BinaryOperator::*;
                 // Lower left-hand side first
-                let lreg = self.lower_node_prog(left, prog, func, current_block);
-                let dest = self.fresh_reg();
+                let lreg = self.lower_value(left, prog, func, current);
                 match operator {
                     LogicalAnd | LogicalOr => {
-                        if let Some(lr) = lreg.clone() {
-                            // Insert placeholder AndSC/OrSC, then inline-lower RHS and patch skip and right register
-                            let and_idx: usize;
-                            if let Some(block) = func.get_block_mut(current_block) {
-                                and_idx = block.instructions.len();
-                                let ins = match operator {
-                                    LogicalAnd => MirInstruction::AndSC {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr.clone()),
-                                        right: MirValue::Null,
-                                        skip: 0,
-                                    },
-                                    LogicalOr => MirInstruction::OrSC {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr.clone()),
-                                        right: MirValue::Null,
-                                        skip: 0,
-                                    },
-                                    _ => unreachable!(),
-                                };
-                                block.instructions.push(ins);
-                            } else {
-                                return None;
-                            }
-
-                            // Record length before lowering RHS
-                            let pre_len = func
-                                .get_block(current_block)
-                                .map(|b| b.instructions.len())
-                                .unwrap_or(0);
-                            let rreg = self.lower_node_prog(right, prog, func, current_block);
-                            // Ensure RHS final value is written into dest to be consumed after short-circuit gate
-                            if let Some(rr) = rreg.clone() {
-                                if let Some(block) = func.get_block_mut(current_block) {
-                                    block.instructions.push(MirInstruction::Move {
-                                        dest: dest.clone(),
-                                        src: rr.clone(),
-                                    });
+                        let dest = self.fresh_reg();
+                        let gate_block = *current;
+                        let gate = func
+                            .get_block(gate_block)
+                            .map_or(0, |b| b.instructions.len());
+                        let ins = match operator {
+                            LogicalAnd => MirInstruction::AndSC {
+                                dest: dest.clone(),
+                                left: reg(&lreg),
+                                right: MirValue::Null,
+                                skip: 0,
+                            },
+                            _ => MirInstruction::OrSC {
+                                dest: dest.clone(),
+                                left: reg(&lreg),
+                                right: MirValue::Null,
+                                skip: 0,
+                            },
+                        };
+                        emit(func, gate_block, ins);
+                        let rreg = self.lower_value(right, prog, func, current);
+                        // Ensure RHS final value is written into dest to be consumed after short-circuit gate
+                        emit(
+                            func,
+                            *current,
+                            MirInstruction::Move {
+                                dest: dest.clone(),
+                                src: rreg.clone(),
+                            },
+                        );
+                        if *current == gate_block {
+                            // The RHS stayed in one block: skip over it on short-circuit
+                            if let Some(block) = func.get_block_mut(gate_block) {
+                                let rhs_count = (block.instructions.len() - gate - 1) as u32;
+                                if let Some(
+                                    MirInstruction::AndSC { skip, right, .. }
+                                    | MirInstruction::OrSC { skip, right, .. },
+                                ) = block.instructions.get_mut(gate)
+                                {
+                                    *skip = rhs_count;
+                                    *right = reg(&rreg);
                                 }
                             }
-                            let post_len = func
-                                .get_block(current_block)
-                                .map(|b| b.instructions.len())
-                                .unwrap_or(pre_len);
-                            let rhs_count = if post_len >= pre_len {
-                                (post_len - pre_len) as u32
-                            } else {
-                                0
-                            };
-
-                            if let Some(block) = func.get_block_mut(current_block) {
-                                if let Some(entry) = block.instructions.get_mut(and_idx) {
-                                    match entry {
-                                        MirInstruction::AndSC { skip, right, .. } => {
-                                            *skip = rhs_count;
-                                            if let Some(rr) = rreg {
-                                                *right = MirValue::Register(rr);
-                                            }
-                                        }
-                                        MirInstruction::OrSC { skip, right, .. } => {
-                                            *skip = rhs_count;
-                                            if let Some(rr) = rreg {
-                                                *right = MirValue::Register(rr);
+                        } else {
+                            // The RHS branches: move its first part out of the gate block and
+                            // short-circuit with a Branch instead
+                            let rhs_block = func.create_block();
+                            let join = func.create_block();
+                            let moved = func.get_block_mut(gate_block).map(|b| {
+                                let tail = b.instructions.split_off(gate + 1);
+                                b.instructions.pop();
+                                (tail, std::mem::take(&mut b.successors))
+                            });
+                            if let Some((tail, successors)) = moved {
+                                for s in &successors {
+                                    if let Some(b) = func.get_block_mut(*s) {
+                                        for p in b.predecessors.iter_mut() {
+                                            if *p == gate_block {
+                                                *p = rhs_block;
                                             }
                                         }
-                                        _ => {}
                                     }
                                 }
+                                if let Some(b) = func.get_block_mut(rhs_block) {
+                                    b.instructions = tail;
+                                    b.successors = successors;
+                                }
                             }
-                        } else if let Some(block) = func.get_block_mut(current_block) {
-                            // Failed to lower LHS; produce null result
-                            block.instructions.push(MirInstruction::LoadImmediate {
-                                dest: dest.clone(),
-                                value: MirValue::Null,
-                            });
-                        }
-                    }
-                    _ => {
-                        let rreg = self.lower_node_prog(right, prog, func, current_block);
-                        if let (Some(lr), Some(rr)) = (lreg.clone(), rreg.clone()) {
-                            if let Some(block) = func.get_block_mut(current_block) {
-                                let ins = match operator {
-                                    Add => MirInstruction::Add {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Subtract => MirInstruction::Sub {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Multiply => MirInstruction::Mul {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Divide => MirInstruction::Div {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Modulo => MirInstruction::Mod {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Power => MirInstruction::Pow {
-                                        dest: dest.clone(),
-                                        base: MirValue::Register(lr),
-                                        exp: MirValue::Register(rr),
-                                    },
-                                    Equal => MirInstruction::Equal {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    NotEqual => MirInstruction::NotEqual {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Less => MirInstruction::LessThan {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    LessEqual => MirInstruction::LessEqual {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Greater => MirInstruction::GreaterThan {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    GreaterEqual => MirInstruction::GreaterEqual {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    BitwiseAnd => MirInstruction::BitAnd {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    BitwiseOr => MirInstruction::BitOr {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    BitwiseXor => MirInstruction::BitXor {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    LeftShift => MirInstruction::Shl {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    RightShift => MirInstruction::Shr {
-                                        dest: dest.clone(),
-                                        left: MirValue::Register(lr),
-                                        right: MirValue::Register(rr),
-                                    },
-                                    Match => MirInstruction::RegexMatch {
-                                        dest: dest.clone(),
-                                        value: MirValue::Register(lr),
-                                        pattern: MirValue::Register(rr),
-                                        not: false,
-                                    },
-                                    NotMatch => MirInstruction::RegexMatch {
-                                        dest: dest.clone(),
-                                        value: MirValue::Register(lr),
-                                        pattern: MirValue::Register(rr),
-                                        not: true,
-                                    },
-                                    LogicalAnd | LogicalOr => unreachable!(),
-                                };
-                                block.instructions.push(ins);
+                            jump(func, *current, join);
+                            let left_truth = self.truthy(&lreg, func, gate_block);
+                            emit(
+                                func,
+                                gate_block,
+                                MirInstruction::Move {
+                                    dest: dest.clone(),
+                                    src: left_truth.clone(),
+                                },
+                            );
+                            if *operator == LogicalAnd {
+                                branch(func, gate_block, left_truth, rhs_block, join);
+                            } else {
+                                branch(func, gate_block, left_truth, join, rhs_block);
                             }
-                        } else if let Some(block) = func.get_block_mut(current_block) {
-                            block.instructions.push(MirInstruction::LoadImmediate {
-                                dest: dest.clone(),
-                                value: MirValue::Null,
-                            });
+                            *current = join;
                         }
+                        Some(dest)
                     }
-                }
-                Some(dest)
-            }
-            AstNode::Match { expr, arms, .. } => {
-                if let Some(val_reg) = self.lower_node_prog(expr, prog, func, current_block) {
-                    let mut arm_pairs = Vec::new();
-                    for (i, arm) in arms.iter().enumerate() {
-                        if let nxsh_parser::ast::Pattern::Literal(lit) = arm.pattern {
-                            arm_pairs.push((MirValue::String(lit.to_string()), i as u32 + 100));
-                        }
-                    }
-                    if let Some(block) = func.get_block_mut(current_block) {
-                        block.instructions.push(MirInstruction::MatchDispatch {
-                            value: MirValue::Register(val_reg),
-                            arms: arm_pairs,
-                            default_block: None,
-                        });
+                    _ => {
+                        let rreg = self.lower_value(right, prog, func, current);
+                        let (l, r) = (reg(&lreg), reg(&rreg));
+                        Some(self.op(func, *current, |dest| match operator {
+                            Add => MirInstruction::Add {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Subtract => MirInstruction::Sub {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Multiply => MirInstruction::Mul {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Divide => MirInstruction::Div {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Modulo => MirInstruction::Mod {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Power => MirInstruction::Pow {
+                                dest,
+                                base: l,
+                                exp: r,
+                            },
+                            Equal => MirInstruction::Equal {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            NotEqual => MirInstruction::NotEqual {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Less => MirInstruction::LessThan {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            LessEqual => MirInstruction::LessEqual {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Greater => MirInstruction::GreaterThan {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            GreaterEqual => MirInstruction::GreaterEqual {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            BitwiseAnd => MirInstruction::BitAnd {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            BitwiseOr => MirInstruction::BitOr {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            BitwiseXor => MirInstruction::BitXor {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            LeftShift => MirInstruction::Shl {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            RightShift => MirInstruction::Shr {
+                                dest,
+                                left: l,
+                                right: r,
+                            },
+                            Match => MirInstruction::RegexMatch {
+                                dest,
+                                value: l,
+                                pattern: r,
+                                not: false,
+                            },
+                            NotMatch => MirInstruction::RegexMatch {
+                                dest,
+                                value: l,
+                                pattern: r,
+                                not: true,
+                            },
+                            LogicalAnd | LogicalOr => unreachable!(),
+                        }))
                     }
                 }
-                None
             }
-            _ => None,
+            AstNode::YieldExpression(value) => value
+                .as_ref()
+                .map(|v| self.lower_value(v, prog, func, current)),
+            // 宣言だけで実行時の処理が無いもの
+            AstNode::MacroDeclaration { .. }
+            | AstNode::ImportStatement { .. }
+            | AstNode::TypeDeclaration { .. }
+            | AstNode::Comment(_)
+            | AstNode::Empty => None,
         }
     }
 }
@@ -0,0 +1,934 @@
+//! MIR optimization pipeline.
+//!
+//! Functions are optimized one at a time by these passes, in order:
+//!
+//! 1. constant folding, propagating constants forward through each block
+//! 2. copy propagation within each block
+//! 3. jump threading: jumps through blocks that only jump are retargeted,
+//!    branches to the same block become jumps and a block is merged into
+//!    the block that jumps to it when that is its only way in
+//! 4. dead code elimination of unreachable blocks, code after a terminator
+//!    and side-effect free instructions whose result is never read
+//!
+//! `MirProgram::optimization_level` selects the passes: 0 leaves the program
+//! as it is, 1 folds constants and removes dead code, 2 (the default) adds
+//! copy propagation and jump threading, and 3 repeats the pipeline until it
+//! stops finding work.
+//!
+//! `AndSC` and `OrSC` skip a fixed number of the instructions after them, so
+//! facts learned inside a skipped range end with it, and removing
+//! instructions recounts the skips that span them.
+
+use super::{
+    const_fold, truthy, MirBasicBlock, MirFunction, MirInstruction, MirProgram, MirRegister,
+    MirValue,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Rounds of the pipeline at level 3 before giving up on a fixed point
+const MAX_ROUNDS: usize = 8;
+
+/// What the optimizer changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizationStats {
+    /// Constant operands propagated and instructions replaced by their result
+    pub constants_folded: usize,
+    /// Register operands replaced by the register they were copied from
+    pub copies_propagated: usize,
+    /// Jump targets retargeted, branches made unconditional and blocks merged
+    pub jumps_threaded: usize,
+    /// Unreachable and merged blocks removed
+    pub blocks_removed: usize,
+    /// Dead instructions removed
+    pub instructions_removed: usize,
+}
+
+impl OptimizationStats {
+    /// Total number of changes made
+    pub fn changes(&self) -> usize {
+        self.constants_folded
+            + self.copies_propagated
+            + self.jumps_threaded
+            + self.blocks_removed
+            + self.instructions_removed
+    }
+
+    fn merge(&mut self, other: &OptimizationStats) {
+        self.constants_folded += other.constants_folded;
+        self.copies_propagated += other.copies_propagated;
+        self.jumps_threaded += other.jumps_threaded;
+        self.blocks_removed += other.blocks_removed;
+        self.instructions_removed += other.instructions_removed;
+    }
+}
+
+/// Optimize every function of `program` at its `optimization_level`
+pub fn optimize_program(program: &mut MirProgram) -> OptimizationStats {
+    let level = program.optimization_level;
+    let mut stats = OptimizationStats::default();
+    for function in program.functions.values_mut() {
+        stats.merge(&optimize_function(function, level));
+    }
+    stats
+}
+
+/// Optimize one function at `level` (0 to 3)
+pub fn optimize_function(func: &mut MirFunction, level: u8) -> OptimizationStats {
+    let mut stats = OptimizationStats::default();
+    if level == 0 {
+        return stats;
+    }
+    let rounds = if level >= 3 { MAX_ROUNDS } else { 1 };
+    for _ in 0..rounds {
+        let before = stats.changes();
+        stats.constants_folded += fold_constants(func);
+        rebuild_edges(func);
+        if level >= 2 {
+            stats.copies_propagated += propagate_copies(func);
+            let (threaded, merged) = thread_jumps(func);
+            stats.jumps_threaded += threaded + merged;
+            stats.blocks_removed += merged;
+            rebuild_edges(func);
+        }
+        let (blocks, instructions) = eliminate_dead_code(func);
+        stats.blocks_removed += blocks;
+        stats.instructions_removed += instructions;
+        rebuild_edges(func);
+        if stats.changes() == before {
+            break;
+        }
+    }
+    stats
+}
+
+/// Control-flow graph of a function, built from its terminators
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    /// Blocks each block can continue to; a block without a terminator
+    /// continues to its first listed successor, which comes first here too
+    pub successors: HashMap<u32, Vec<u32>>,
+    /// Blocks that can continue to each block
+    pub predecessors: HashMap<u32, Vec<u32>>,
+    /// Blocks entered other than from a predecessor: try handlers, closure
+    /// bodies and background jobs
+    pub side_entries: HashSet<u32>,
+    /// Blocks reachable from the entry, in reverse postorder
+    pub order: Vec<u32>,
+    reachable: HashSet<u32>,
+}
+
+impl Cfg {
+    pub fn build(func: &MirFunction) -> Self {
+        let mut cfg = Cfg::default();
+        let mut entered: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut ids: Vec<u32> = func.blocks.keys().copied().collect();
+        ids.sort_unstable();
+        for &id in &ids {
+            let block = &func.blocks[&id];
+            let mut targets: Vec<u32> = Vec::new();
+            if falls_through(block) {
+                targets.extend(block.successors.first());
+            }
+            for target in jump_targets(block) {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            targets.retain(|target| func.blocks.contains_key(target));
+            for &target in &targets {
+                cfg.predecessors.entry(target).or_default().push(id);
+            }
+            cfg.successors.insert(id, targets);
+            let side: Vec<u32> = side_entries(block)
+                .into_iter()
+                .filter(|target| func.blocks.contains_key(target))
+                .collect();
+            cfg.side_entries.extend(&side);
+            entered.insert(id, side);
+        }
+
+        // Depth-first from the entry; side entries are reached from the
+        // block that refers to them
+        let mut postorder = Vec::new();
+        let mut stack = vec![(func.entry_block, false)];
+        while let Some((id, done)) = stack.pop() {
+            if done {
+                postorder.push(id);
+                continue;
+            }
+            if !func.blocks.contains_key(&id) || !cfg.reachable.insert(id) {
+                continue;
+            }
+            stack.push((id, true));
+            let next = cfg.successors[&id].iter().chain(&entered[&id]);
+            for &target in next.rev() {
+                if !cfg.reachable.contains(&target) {
+                    stack.push((target, false));
+                }
+            }
+        }
+        postorder.reverse();
+        cfg.order = postorder;
+        cfg
+    }
+
+    pub fn is_reachable(&self, block: u32) -> bool {
+        self.reachable.contains(&block)
+    }
+}
+
+/// Replace instructions whose operands are constants by their result,
+/// propagating constants forward through each block. Branches on a constant
+/// become jumps and short-circuit operators with a constant left operand are
+/// resolved. Returns the number of rewrites
+pub fn fold_constants(func: &mut MirFunction) -> usize {
+    let mut folded = 0;
+    for block in func.blocks.values_mut() {
+        folded += scan(block, |inst, facts| {
+            let mut changed = substitute(inst, |reg| match facts.get(reg) {
+                Some(Known::Constant(value)) => Some(value.clone()),
+                _ => None,
+            });
+            if let MirInstruction::Move { dest, src } = inst {
+                if let Some(Known::Constant(value)) = facts.get(src) {
+                    let (dest, value) = (dest.clone(), value.clone());
+                    *inst = MirInstruction::LoadImmediate { dest, value };
+                    changed += 1;
+                }
+            }
+            if let Some(value) = const_fold::fold(inst) {
+                if let Some(dest) = defs(inst).pop() {
+                    *inst = MirInstruction::LoadImmediate { dest, value };
+                    changed += 1;
+                }
+            }
+            if let MirInstruction::Branch {
+                condition,
+                true_block,
+                false_block,
+            } = inst
+            {
+                if const_fold::is_constant(condition) {
+                    let target = if truthy(condition) {
+                        *true_block
+                    } else {
+                        *false_block
+                    };
+                    *inst = MirInstruction::Jump { target };
+                    changed += 1;
+                }
+            }
+            let learned = match inst {
+                MirInstruction::LoadImmediate { dest, value } if const_fold::is_constant(value) => {
+                    Some((dest.clone(), Known::Constant(value.clone())))
+                }
+                _ => None,
+            };
+            (changed, learned)
+        });
+        folded += fold_short_circuits(block);
+    }
+    folded
+}
+
+/// Replace uses of registers that hold a copy of another register by that
+/// register, within each block. Returns the number of operands replaced
+pub fn propagate_copies(func: &mut MirFunction) -> usize {
+    let mut propagated = 0;
+    for block in func.blocks.values_mut() {
+        propagated += scan(block, |inst, facts| {
+            let mut changed = substitute(inst, |reg| match facts.get(reg) {
+                Some(Known::Copy(source)) => Some(MirValue::Register(source.clone())),
+                _ => None,
+            });
+            if let MirInstruction::Move { src, .. } | MirInstruction::PipelineAdd { command: src } =
+                inst
+            {
+                if let Some(Known::Copy(source)) = facts.get(src) {
+                    *src = source.clone();
+                    changed += 1;
+                }
+            }
+            let learned = match inst {
+                MirInstruction::Move { dest, src } if dest != src => {
+                    Some((dest.clone(), Known::Copy(src.clone())))
+                }
+                _ => None,
+            };
+            (changed, learned)
+        });
+    }
+    propagated
+}
+
+/// Retarget jumps through blocks that only continue to another block, turn
+/// branches with both targets alike into jumps and merge blocks into their
+/// only predecessor. Returns the number of targets changed and of blocks merged
+pub fn thread_jumps(func: &mut MirFunction) -> (usize, usize) {
+    let forwards: HashMap<u32, u32> = func
+        .blocks
+        .iter()
+        .filter_map(|(&id, block)| {
+            let target = match block.instructions.as_slice() {
+                [MirInstruction::Jump { target }] => *target,
+                [] => *block.successors.first()?,
+                _ => return None,
+            };
+            (target != id).then_some((id, target))
+        })
+        .collect();
+    let resolve = |mut id: u32| {
+        let mut seen = HashSet::new();
+        while let Some(&next) = forwards.get(&id) {
+            if !seen.insert(id) {
+                break;
+            }
+            id = next;
+        }
+        id
+    };
+
+    let mut threaded = 0;
+    for block in func.blocks.values_mut() {
+        for inst in &mut block.instructions {
+            let targets: Vec<&mut u32> = match inst {
+                MirInstruction::Jump { target } => vec![target],
+                MirInstruction::Branch {
+                    true_block,
+                    false_block,
+                    ..
+                } => vec![true_block, false_block],
+                MirInstruction::MatchDispatch {
+                    arms,
+                    default_block,
+                    ..
+                } => arms
+                    .iter_mut()
+                    .map(|(_, block)| block)
+                    .chain(default_block.as_mut())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for target in targets {
+                let resolved = resolve(*target);
+                if resolved != *target {
+                    *target = resolved;
+                    threaded += 1;
+                }
+            }
+            // Evaluating a condition has no effect, so both ways being the
+            // same block makes it a jump
+            if let MirInstruction::Branch {
+                true_block,
+                false_block,
+                ..
+            } = inst
+            {
+                if true_block == false_block {
+                    let target = *true_block;
+                    *inst = MirInstruction::Jump { target };
+                    threaded += 1;
+                }
+            }
+        }
+        if falls_through(block) {
+            if let Some(next) = block.successors.first_mut() {
+                let resolved = resolve(*next);
+                if resolved != *next {
+                    *next = resolved;
+                    threaded += 1;
+                }
+            }
+        }
+    }
+    (threaded, merge_blocks(func))
+}
+
+/// Remove unreachable blocks, instructions after a terminator and side-effect
+/// free instructions whose results are never read. Returns the number of
+/// blocks and of instructions removed
+pub fn eliminate_dead_code(func: &mut MirFunction) -> (usize, usize) {
+    let cfg = Cfg::build(func);
+    let blocks_before = func.blocks.len();
+    func.blocks.retain(|id, _| cfg.is_reachable(*id));
+    let blocks_removed = blocks_before - func.blocks.len();
+
+    let mut removed = 0;
+    for block in func.blocks.values_mut() {
+        if let Some(end) = end_of_block(block) {
+            removed += block.instructions.len() - end;
+            block.instructions.truncate(end);
+        }
+        let nops: Vec<bool> = block
+            .instructions
+            .iter()
+            .map(|inst| matches!(inst, MirInstruction::Nop))
+            .collect();
+        removed += remove_instructions(block, &nops);
+    }
+
+    // Removing one unused result can leave the registers it read unused
+    loop {
+        let read: HashSet<MirRegister> = func
+            .blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .flat_map(reads)
+            .collect();
+        let mut pass = 0;
+        for block in func.blocks.values_mut() {
+            let dead: Vec<bool> = block
+                .instructions
+                .iter()
+                .map(|inst| is_pure(inst) && defs(inst).iter().all(|reg| !read.contains(reg)))
+                .collect();
+            pass += remove_instructions(block, &dead);
+        }
+        if pass == 0 {
+            break;
+        }
+        removed += pass;
+    }
+    (blocks_removed, removed)
+}
+
+/// Reset every block's successor and predecessor lists from its terminators
+fn rebuild_edges(func: &mut MirFunction) {
+    let mut cfg = Cfg::build(func);
+    for (id, block) in func.blocks.iter_mut() {
+        block.successors = cfg.successors.remove(id).unwrap_or_default();
+        block.predecessors = cfg.predecessors.remove(id).unwrap_or_default();
+    }
+}
+
+/// Append blocks to the block that jumps to them when that jump is their
+/// only way in. Returns the number of blocks merged
+fn merge_blocks(func: &mut MirFunction) -> usize {
+    let mut merged = 0;
+    loop {
+        let cfg = Cfg::build(func);
+        let candidate = cfg.order.iter().find_map(|&id| {
+            let block = func.get_block(id)?;
+            let last = block.instructions.len().checked_sub(1)?;
+            let MirInstruction::Jump { target } = block.instructions[last] else {
+                return None;
+            };
+            // Unreachable blocks are removed next and are no way in
+            let mut ways_in = cfg
+                .predecessors
+                .get(&target)?
+                .iter()
+                .filter(|&&p| cfg.is_reachable(p));
+            let only_way_in = ways_in.next() == Some(&id)
+                && ways_in.next().is_none()
+                && jump_targets(block).iter().filter(|&&t| t == target).count() == 1;
+            (only_way_in
+                && target != id
+                && target != func.entry_block
+                && !cfg.side_entries.contains(&target)
+                && !skipped(block)[last])
+                .then_some((id, target))
+        });
+        let Some((id, target)) = candidate else {
+            break;
+        };
+        let Some(absorbed) = func.blocks.remove(&target) else {
+            break;
+        };
+        if let Some(block) = func.blocks.get_mut(&id) {
+            block.instructions.pop();
+            block.instructions.extend(absorbed.instructions);
+            block.successors = absorbed.successors;
+        }
+        merged += 1;
+    }
+    merged
+}
+
+/// Replace each short-circuit operator whose left operand is a constant:
+/// by nothing when its right-hand side always runs, otherwise by its result
+/// with the right-hand side removed
+fn fold_short_circuits(block: &mut MirBasicBlock) -> usize {
+    let mut folded = 0;
+    loop {
+        let found = block
+            .instructions
+            .iter()
+            .enumerate()
+            .find_map(|(index, inst)| Some((index, const_fold::needs_right(inst)?)));
+        let Some((index, needs_right)) = found else {
+            break;
+        };
+        let mut remove = vec![false; block.instructions.len()];
+        match &block.instructions[index] {
+            MirInstruction::AndSC { dest, skip, .. } | MirInstruction::OrSC { dest, skip, .. }
+                if !needs_right =>
+            {
+                let last = (index + *skip as usize).min(remove.len() - 1);
+                remove[index + 1..=last].fill(true);
+                // A skipped right-hand side leaves the left operand's truth value
+                let value = MirValue::Boolean(matches!(
+                    block.instructions[index],
+                    MirInstruction::OrSC { .. }
+                ));
+                block.instructions[index] = MirInstruction::LoadImmediate {
+                    dest: dest.clone(),
+                    value,
+                };
+            }
+            _ => remove[index] = true,
+        }
+        remove_instructions(block, &remove);
+        folded += 1;
+    }
+    folded
+}
+
+/// What is known about a register at a point in a block
+#[derive(Debug, Clone)]
+enum Known {
+    Constant(MirValue),
+    Copy(MirRegister),
+}
+
+/// Facts about registers while walking a block
+#[derive(Default)]
+struct Facts {
+    /// Each fact with the last instruction it holds for
+    known: HashMap<MirRegister, (Known, usize)>,
+    /// Last instructions of the skip ranges around the current instruction
+    ranges: Vec<usize>,
+}
+
+impl Facts {
+    /// Move to instruction `index`, forgetting facts learned in skip ranges
+    /// that have ended
+    fn advance(&mut self, index: usize) {
+        self.ranges.retain(|&last| last >= index);
+        self.known.retain(|_, (_, until)| *until >= index);
+    }
+
+    fn get(&self, reg: &MirRegister) -> Option<&Known> {
+        self.known.get(reg).map(|(known, _)| known)
+    }
+
+    /// A fact learned inside a skip range holds only until the range ends,
+    /// since the instruction it came from may not have run
+    fn learn(&mut self, reg: MirRegister, known: Known) {
+        let until = self.ranges.iter().copied().min().unwrap_or(usize::MAX);
+        self.known.insert(reg, (known, until));
+    }
+
+    /// `reg` was written: forget what was known about it and about copies of it
+    fn kill(&mut self, reg: &MirRegister) {
+        self.known.remove(reg);
+        self.known
+            .retain(|_, (known, _)| !matches!(known, Known::Copy(source) if source == reg));
+    }
+}
+
+/// Walk `block` forward. `step` rewrites each instruction using the facts
+/// known before it and returns how many changes it made and any fact the
+/// rewritten instruction establishes
+fn scan(
+    block: &mut MirBasicBlock,
+    mut step: impl FnMut(&mut MirInstruction, &Facts) -> (usize, Option<(MirRegister, Known)>),
+) -> usize {
+    let mut facts = Facts::default();
+    let mut changes = 0;
+    for (index, inst) in block.instructions.iter_mut().enumerate() {
+        facts.advance(index);
+        let (changed, learned) = step(inst, &facts);
+        changes += changed;
+        for reg in defs(inst) {
+            facts.kill(&reg);
+        }
+        if let Some((reg, known)) = learned {
+            facts.learn(reg, known);
+        }
+        if let MirInstruction::AndSC { skip, .. } | MirInstruction::OrSC { skip, .. } = inst {
+            facts.ranges.push(index + *skip as usize);
+        }
+    }
+    changes
+}
+
+/// Replace register operands for which `known` has a value
+fn substitute(
+    inst: &mut MirInstruction,
+    known: impl Fn(&MirRegister) -> Option<MirValue>,
+) -> usize {
+    let mut replaced = 0;
+    for operand in operands_mut(inst) {
+        if let MirValue::Register(reg) = operand {
+            if let Some(value) = known(reg) {
+                *operand = value;
+                replaced += 1;
+            }
+        }
+    }
+    replaced
+}
+
+/// Remove the instructions marked in `remove`, shrinking the skips of
+/// short-circuit operators that span them. Returns the number removed
+fn remove_instructions(block: &mut MirBasicBlock, remove: &[bool]) -> usize {
+    let removed = remove.iter().filter(|&&r| r).count();
+    if removed == 0 {
+        return 0;
+    }
+    // kept[i]: instructions kept before index i
+    let mut kept = vec![0; remove.len() + 1];
+    for (i, &r) in remove.iter().enumerate() {
+        kept[i + 1] = kept[i] + usize::from(!r);
+    }
+    let len = block.instructions.len();
+    for (i, inst) in block.instructions.iter_mut().enumerate() {
+        if let MirInstruction::AndSC { skip, .. } | MirInstruction::OrSC { skip, .. } = inst {
+            let last = (i + *skip as usize).min(len - 1);
+            *skip = (kept[last + 1] - kept[i + 1]) as u32;
+        }
+    }
+    let mut index = 0;
+    block.instructions.retain(|_| {
+        let keep = !remove[index];
+        index += 1;
+        keep
+    });
+    removed
+}
+
+fn is_terminator(inst: &MirInstruction) -> bool {
+    matches!(
+        inst,
+        MirInstruction::Jump { .. }
+            | MirInstruction::Branch { .. }
+            | MirInstruction::Return { .. }
+            | MirInstruction::ClosureReturn { .. }
+            | MirInstruction::Throw { .. }
+            | MirInstruction::Unreachable
+    )
+}
+
+/// Instructions a short-circuit operator may skip
+fn skipped(block: &MirBasicBlock) -> Vec<bool> {
+    let len = block.instructions.len();
+    let mut skipped = vec![false; len];
+    for (i, inst) in block.instructions.iter().enumerate() {
+        if let MirInstruction::AndSC { skip, .. } | MirInstruction::OrSC { skip, .. } = inst {
+            let last = (i + *skip as usize).min(len - 1);
+            skipped[i + 1..=last.max(i)].fill(true);
+        }
+    }
+    skipped
+}
+
+/// Length of `block` up to its first terminator that always runs
+fn end_of_block(block: &MirBasicBlock) -> Option<usize> {
+    let skipped = skipped(block);
+    block
+        .instructions
+        .iter()
+        .enumerate()
+        .position(|(i, inst)| is_terminator(inst) && !skipped[i])
+        .map(|i| i + 1)
+}
+
+/// Whether running `block` can reach its end and continue to its first successor
+fn falls_through(block: &MirBasicBlock) -> bool {
+    end_of_block(block).is_none()
+}
+
+/// Blocks the reachable instructions of `block` jump or branch to
+fn jump_targets(block: &MirBasicBlock) -> Vec<u32> {
+    let end = end_of_block(block).unwrap_or(block.instructions.len());
+    let mut targets = Vec::new();
+    for inst in &block.instructions[..end] {
+        match inst {
+            MirInstruction::Jump { target } => targets.push(*target),
+            MirInstruction::Branch {
+                true_block,
+                false_block,
+                ..
+            } => targets.extend([*true_block, *false_block]),
+            MirInstruction::MatchDispatch {
+                arms,
+                default_block,
+                ..
+            } => {
+                targets.extend(arms.iter().map(|(_, block)| *block));
+                targets.extend(*default_block);
+            }
+            _ => {}
+        }
+    }
+    targets
+}
+
+/// Blocks `block` starts running other than by jumping to them
+fn side_entries(block: &MirBasicBlock) -> Vec<u32> {
+    block
+        .instructions
+        .iter()
+        .filter_map(|inst| match inst {
+            MirInstruction::TryBegin { handler_block } => Some(*handler_block),
+            MirInstruction::Background { block, .. } | MirInstruction::Capture { block, .. } => {
+                Some(*block)
+            }
+            MirInstruction::ClosureCreate { func_block, .. } => Some(*func_block),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Instructions that only write their destination and cannot fail
+fn is_pure(inst: &MirInstruction) -> bool {
+    matches!(
+        inst,
+        MirInstruction::LoadImmediate { .. }
+            | MirInstruction::Move { .. }
+            | MirInstruction::Load { .. }
+            | MirInstruction::Not { .. }
+            | MirInstruction::Status { .. }
+            | MirInstruction::Equal { .. }
+            | MirInstruction::NotEqual { .. }
+            | MirInstruction::Concat { .. }
+            | MirInstruction::StringLength { .. }
+            | MirInstruction::Substring { .. }
+            | MirInstruction::MakeArray { .. }
+            | MirInstruction::ArrayGet { .. }
+            | MirInstruction::ArrayLength { .. }
+            | MirInstruction::MakeObject { .. }
+            | MirInstruction::ObjectGet { .. }
+            | MirInstruction::GetIterator { .. }
+            | MirInstruction::Caught { .. }
+            | MirInstruction::ClosureCreate { .. }
+    )
+}
+
+/// Registers an instruction writes, including arrays, objects and iterators
+/// it updates in place
+fn defs(inst: &MirInstruction) -> Vec<MirRegister> {
+    use MirInstruction::*;
+    match inst {
+        LoadImmediate { dest, .. }
+        | Move { dest, .. }
+        | Load { dest, .. }
+        | Add { dest, .. }
+        | Sub { dest, .. }
+        | Mul { dest, .. }
+        | Div { dest, .. }
+        | Mod { dest, .. }
+        | Pow { dest, .. }
+        | BitAnd { dest, .. }
+        | BitOr { dest, .. }
+        | BitXor { dest, .. }
+        | Shl { dest, .. }
+        | Shr { dest, .. }
+        | Compare { dest, .. }
+        | And { dest, .. }
+        | Or { dest, .. }
+        | AndSC { dest, .. }
+        | OrSC { dest, .. }
+        | Not { dest, .. }
+        | Subtract { dest, .. }
+        | Multiply { dest, .. }
+        | Divide { dest, .. }
+        | Modulo { dest, .. }
+        | Equal { dest, .. }
+        | NotEqual { dest, .. }
+        | LessThan { dest, .. }
+        | LessEqual { dest, .. }
+        | GreaterThan { dest, .. }
+        | GreaterEqual { dest, .. }
+        | Concat { dest, .. }
+        | StringLength { dest, .. }
+        | Substring { dest, .. }
+        | MakeArray { dest, .. }
+        | ArrayGet { dest, .. }
+        | ArrayLength { dest, .. }
+        | MakeObject { dest, .. }
+        | ObjectGet { dest, .. }
+        | Call { dest, .. }
+        | TailCall { dest, .. }
+        | SystemCall { dest, .. }
+        | ExecuteCommand { dest, .. }
+        | ExecutePipeline { dest, .. }
+        | PipelineExec { dest }
+        | Status { dest, .. }
+        | Background { dest, .. }
+        | Capture { dest, .. }
+        | Phi { dest, .. }
+        | GetIterator { dest, .. }
+        | Caught { dest }
+        | ClosureCreate { dest, .. }
+        | ClosureCall { dest, .. }
+        | RegexMatch { dest, .. } => vec![dest.clone()],
+        ArraySet {
+            array: MirValue::Register(target),
+            ..
+        }
+        | ObjectSet {
+            object: MirValue::Register(target),
+            ..
+        } => vec![target.clone()],
+        IteratorNext {
+            iterator,
+            element,
+            has_next,
+        } => {
+            let mut written = vec![element.clone(), has_next.clone()];
+            if let MirValue::Register(target) = iterator {
+                written.push(target.clone());
+            }
+            written
+        }
+        MacroExpand { inner } => defs(inner),
+        _ => Vec::new(),
+    }
+}
+
+/// Value operands an instruction reads. Registers updated in place and
+/// operands held as plain registers are not included, since they cannot be
+/// replaced by a value. The `right` of a short-circuit operator is not read:
+/// it only names the register its right-hand side leaves the result in
+fn operands_mut(inst: &mut MirInstruction) -> Vec<&mut MirValue> {
+    use MirInstruction::*;
+    match inst {
+        LoadImmediate { value, .. }
+        | Store { value, .. }
+        | Status { value, .. }
+        | Throw { value }
+        | RedirectPush { target: value, .. }
+        | DefineFunction {
+            function: value, ..
+        }
+        | Not { operand: value, .. }
+        | AndSC { left: value, .. }
+        | OrSC { left: value, .. }
+        | Branch {
+            condition: value, ..
+        }
+        | StringLength { string: value, .. }
+        | ArrayLength { array: value, .. }
+        | ObjectGet { object: value, .. }
+        | ObjectSet { value, .. }
+        | GetIterator {
+            iterable: value, ..
+        } => vec![value],
+        Add { left, right, .. }
+        | Sub { left, right, .. }
+        | Mul { left, right, .. }
+        | Div { left, right, .. }
+        | Mod { left, right, .. }
+        | Pow {
+            base: left,
+            exp: right,
+            ..
+        }
+        | BitAnd { left, right, .. }
+        | BitOr { left, right, .. }
+        | BitXor { left, right, .. }
+        | Shl { left, right, .. }
+        | Shr { left, right, .. }
+        | Compare { left, right, .. }
+        | And { left, right, .. }
+        | Or { left, right, .. }
+        | Subtract { left, right, .. }
+        | Multiply { left, right, .. }
+        | Divide { left, right, .. }
+        | Modulo { left, right, .. }
+        | Equal { left, right, .. }
+        | NotEqual { left, right, .. }
+        | LessThan { left, right, .. }
+        | LessEqual { left, right, .. }
+        | GreaterThan { left, right, .. }
+        | GreaterEqual { left, right, .. }
+        | ArrayGet {
+            array: left,
+            index: right,
+            ..
+        }
+        | ArraySet {
+            index: left,
+            value: right,
+            ..
+        }
+        | RegexMatch {
+            value: left,
+            pattern: right,
+            ..
+        } => vec![left, right],
+        Return { value } | ClosureReturn { value } => value.iter_mut().collect(),
+        Concat { parts: values, .. }
+        | MakeArray {
+            elements: values, ..
+        }
+        | Call { args: values, .. }
+        | TailCall { args: values, .. }
+        | SystemCall { args: values, .. }
+        | ExecuteCommand { args: values, .. }
+        | ExecutePipeline {
+            commands: values, ..
+        }
+        | ClosureCreate {
+            captures: values, ..
+        } => values.iter_mut().collect(),
+        Substring {
+            string,
+            start,
+            length,
+            ..
+        } => {
+            let mut values = vec![string, start];
+            values.extend(length.as_mut());
+            values
+        }
+        MakeObject { fields, .. } => fields.iter_mut().map(|(_, value)| value).collect(),
+        MatchDispatch { value, arms, .. } => {
+            let mut values = vec![value];
+            values.extend(arms.iter_mut().map(|(arm, _)| arm));
+            values
+        }
+        ClosureCall { closure, args, .. } => {
+            let mut values = vec![closure];
+            values.extend(args.iter_mut());
+            values
+        }
+        MacroExpand { inner } => operands_mut(inner),
+        _ => Vec::new(),
+    }
+}
+
+/// Every register an instruction reads, including registers nested in
+/// array and object operands and registers it updates in place
+fn reads(inst: &MirInstruction) -> Vec<MirRegister> {
+    fn registers_in(value: &MirValue, out: &mut Vec<MirRegister>) {
+        match value {
+            MirValue::Register(reg) => out.push(reg.clone()),
+            MirValue::Array(items) => items.iter().for_each(|item| registers_in(item, out)),
+            MirValue::Object(fields) => fields.values().for_each(|field| registers_in(field, out)),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    match inst {
+        MirInstruction::Move { src, .. } | MirInstruction::PipelineAdd { command: src } => {
+            out.push(src.clone())
+        }
+        MirInstruction::Phi { values, .. } => out.extend(values.iter().map(|(reg, _)| reg.clone())),
+        MirInstruction::ArraySet { array: target, .. }
+        | MirInstruction::ObjectSet { object: target, .. }
+        | MirInstruction::IteratorNext {
+            iterator: target, ..
+        } => registers_in(target, &mut out),
+        MirInstruction::MacroExpand { inner } => out.extend(reads(inner)),
+        _ => {}
+    }
+    // operands_mut needs a mutable instruction; reading does not change it
+    let mut copy = inst.clone();
+    for operand in operands_mut(&mut copy) {
+        registers_in(operand, &mut out);
+    }
+    out
+}
@@ -0,0 +1,211 @@
+//! Execution profile of lowered MIR programs.
+//!
+//! While profiling is on, `MirExecutor` counts every instruction it runs by
+//! opcode, function and basic block, and times every function call. Time is
+//! cumulative: it includes the functions called, and a recursive function is
+//! only timed at its outermost call. The profile serializes to JSON for
+//! external analysis.
+
+use super::MirInstruction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::mem::Discriminant;
+use std::time::Instant;
+
+/// What ran while profiling was on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Instructions executed
+    pub instructions_executed: u64,
+    /// Calls and time by function name
+    pub functions: BTreeMap<String, FunctionProfile>,
+    /// Instructions executed by opcode
+    pub opcodes: BTreeMap<String, u64>,
+    /// Opcode names already looked up
+    #[serde(skip)]
+    opcode_names: HashMap<Discriminant<MirInstruction>, String>,
+    /// Calls running, innermost last
+    #[serde(skip)]
+    active: Vec<(String, Instant)>,
+}
+
+/// Calls, time and blocks of one function
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Cumulative time of its calls in nanoseconds
+    pub total_ns: u64,
+    pub instructions: u64,
+    /// Entries and instructions by block id
+    pub blocks: BTreeMap<u32, BlockProfile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockProfile {
+    pub entries: u64,
+    pub instructions: u64,
+}
+
+/// A block with the instructions executed in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotBlock<'a> {
+    pub function: &'a str,
+    pub block: u32,
+    pub profile: &'a BlockProfile,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Functions by cumulative time, longest first
+    pub fn top_functions(&self, top: usize) -> Vec<(&str, &FunctionProfile)> {
+        let mut functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+            .collect();
+        functions.sort_by(|a, b| b.1.total_ns.cmp(&a.1.total_ns).then(a.0.cmp(b.0)));
+        functions.truncate(top);
+        functions
+    }
+
+    /// Blocks by instructions executed in them, most first
+    pub fn hot_blocks(&self, top: usize) -> Vec<HotBlock<'_>> {
+        let mut blocks: Vec<_> = self
+            .functions
+            .iter()
+            .flat_map(|(function, profile)| {
+                profile.blocks.iter().map(|(block, profile)| HotBlock {
+                    function,
+                    block: *block,
+                    profile,
+                })
+            })
+            .collect();
+        blocks.sort_by(|a, b| {
+            b.profile
+                .instructions
+                .cmp(&a.profile.instructions)
+                .then(a.function.cmp(b.function))
+                .then(a.block.cmp(&b.block))
+        });
+        blocks.truncate(top);
+        blocks
+    }
+
+    /// Opcodes by instructions executed, most first
+    pub fn top_opcodes(&self, top: usize) -> Vec<(&str, u64)> {
+        let mut opcodes: Vec<_> = self
+            .opcodes
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        opcodes.truncate(top);
+        opcodes
+    }
+
+    /// Text report with the `top` entries of each table
+    pub fn report(&self, top: usize) -> String {
+        let mut out = String::new();
+        let calls: u64 = self.functions.values().map(|f| f.calls).sum();
+        let _ = writeln!(
+            out,
+            "{} instructions, {} calls",
+            self.instructions_executed, calls
+        );
+        let _ = writeln!(out, "\nFunctions by cumulative time");
+        let _ = writeln!(
+            out,
+            "{:>10} {:>12} {:>14}  function",
+            "calls", "time ms", "instructions"
+        );
+        for (name, f) in self.top_functions(top) {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>12.3} {:>14}  {name}",
+                f.calls,
+                f.total_ns as f64 / 1_000_000.0,
+                f.instructions
+            );
+        }
+        let _ = writeln!(out, "\nHot blocks");
+        let _ = writeln!(out, "{:>10} {:>14}  block", "entries", "instructions");
+        for hot in self.hot_blocks(top) {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>14}  {}:block_{}",
+                hot.profile.entries, hot.profile.instructions, hot.function, hot.block
+            );
+        }
+        let _ = writeln!(out, "\nInstructions by opcode");
+        let _ = writeln!(out, "{:>14}  opcode", "count");
+        for (name, count) in self.top_opcodes(top) {
+            let _ = writeln!(out, "{count:>14}  {name}");
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    pub(crate) fn enter(&mut self, function: &str) {
+        self.function(function).calls += 1;
+        self.active.push((function.to_string(), Instant::now()));
+    }
+
+    pub(crate) fn leave(&mut self) {
+        let Some((function, started)) = self.active.pop() else {
+            return;
+        };
+        // 再帰呼び出しは一番外側の呼び出しだけで計る
+        if self.active.iter().all(|(name, _)| *name != function) {
+            self.function(&function).total_ns += started.elapsed().as_nanos() as u64;
+        }
+    }
+
+    pub(crate) fn block(&mut self, function: &str, block: u32) {
+        self.function(function)
+            .blocks
+            .entry(block)
+            .or_default()
+            .entries += 1;
+    }
+
+    pub(crate) fn instruction(&mut self, function: &str, block: u32, inst: &MirInstruction) {
+        self.instructions_executed += 1;
+        let profile = self.function(function);
+        profile.instructions += 1;
+        profile.blocks.entry(block).or_default().instructions += 1;
+        let name = self
+            .opcode_names
+            .entry(std::mem::discriminant(inst))
+            .or_insert_with(|| opcode_name(inst));
+        match self.opcodes.get_mut(name.as_str()) {
+            Some(count) => *count += 1,
+            None => {
+                self.opcodes.insert(name.clone(), 1);
+            }
+        }
+    }
+
+    fn function(&mut self, function: &str) -> &mut FunctionProfile {
+        if !self.functions.contains_key(function) {
+            self.functions
+                .insert(function.to_string(), FunctionProfile::default());
+        }
+        self.functions.get_mut(function).expect("inserted above")
+    }
+}
+
+/// Variant name of an instruction, such as `LoadImmediate`
+fn opcode_name(inst: &MirInstruction) -> String {
+    format!("{inst:?}")
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
@@ -0,0 +1,243 @@
+//! Commands provided by plugins
+//!
+//! A plugin system registers each command a loaded plugin declares, with its
+//! help and completion spec, in the [`PluginCommandRegistry`] of the shell
+//! state. The executor consults the registry after functions and builtins,
+//! before searching `$PATH`, so `myplugin-cmd args` runs the plugin without
+//! the command being registered on every new executor. Builtins keep their
+//! names: a plugin command with the name of a builtin is never reached.
+//!
+//! Plugins also register [`PluginCompleter`]s, which the line editor asks for
+//! the arguments of a command, and [`PromptSegment`]s, which prompt templates
+//! show as `{plugin:NAME}`.
+
+use crate::context::ShellContext;
+use crate::error::ShellResult;
+use crate::executor::ExecutionResult;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Runs a plugin command with its arguments
+pub type PluginCommandHandler =
+    Arc<dyn Fn(&mut ShellContext, &[String]) -> ShellResult<ExecutionResult> + Send + Sync>;
+
+/// How the line editor completes a command's arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionSpec {
+    /// Usage line, whose placeholders (`FILE`, `DIR`, ...) give the kind of
+    /// argument the command takes
+    pub usage: String,
+    /// Flags and their descriptions
+    pub flags: Vec<(String, String)>,
+}
+
+/// A command a plugin declares
+#[derive(Clone)]
+pub struct PluginCommand {
+    /// Name the command is run by
+    pub name: String,
+    /// Plugin providing the command
+    pub plugin: String,
+    /// One-line description
+    pub synopsis: String,
+    /// Longer help text
+    pub help: String,
+    /// Argument completion
+    pub completion: CompletionSpec,
+    /// Runs the command
+    pub handler: PluginCommandHandler,
+}
+
+impl PluginCommand {
+    /// Run the command with `args`
+    pub fn execute(
+        &self,
+        context: &mut ShellContext,
+        args: &[String],
+    ) -> ShellResult<ExecutionResult> {
+        (self.handler)(context, args)
+    }
+}
+
+impl std::fmt::Debug for PluginCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginCommand")
+            .field("name", &self.name)
+            .field("plugin", &self.plugin)
+            .field("synopsis", &self.synopsis)
+            .field("completion", &self.completion)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Completes the line being edited with the cursor at the given byte
+/// offset, returning candidates for the word under the cursor
+pub type CompletionProvider = Arc<dyn Fn(&str, usize) -> Vec<String> + Send + Sync>;
+
+/// Computes the text of a prompt segment in the given working directory;
+/// empty for nothing
+pub type SegmentRenderer = Arc<dyn Fn(&Path) -> String + Send + Sync>;
+
+/// Completion a plugin provides for the arguments of a command
+#[derive(Clone)]
+pub struct PluginCompleter {
+    /// Command whose arguments it completes
+    pub command: String,
+    /// Plugin providing it
+    pub plugin: String,
+    pub complete: CompletionProvider,
+}
+
+impl std::fmt::Debug for PluginCompleter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginCompleter")
+            .field("command", &self.command)
+            .field("plugin", &self.plugin)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A prompt segment a plugin provides
+#[derive(Clone)]
+pub struct PromptSegment {
+    /// Name templates show it by, as `{plugin:NAME}`
+    pub name: String,
+    /// Plugin providing it
+    pub plugin: String,
+    /// How long the prompt waits for it before showing its last value;
+    /// when unset, as long as for its other slow segments
+    pub timeout: Option<Duration>,
+    pub render: SegmentRenderer,
+}
+
+impl std::fmt::Debug for PromptSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PromptSegment")
+            .field("name", &self.name)
+            .field("plugin", &self.plugin)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What plugins registered, each by the name it is looked up by
+#[derive(Default)]
+struct Registered {
+    commands: BTreeMap<String, PluginCommand>,
+    completers: BTreeMap<String, PluginCompleter>,
+    segments: BTreeMap<String, PromptSegment>,
+}
+
+/// Plugin commands by name, with the completers and prompt segments of
+/// plugins, shared between a context, its subcontexts and the state it is
+/// saved to, so what is registered once stays available
+#[derive(Clone, Default)]
+pub struct PluginCommandRegistry(Arc<RwLock<Registered>>);
+
+impl PluginCommandRegistry {
+    /// Add `command`, returning the command it replaces
+    pub fn register(&self, command: PluginCommand) -> Option<PluginCommand> {
+        self.0
+            .write()
+            .ok()?
+            .commands
+            .insert(command.name.clone(), command)
+    }
+
+    /// Add `completer`, returning the completer of the same command it replaces
+    pub fn register_completer(&self, completer: PluginCompleter) -> Option<PluginCompleter> {
+        self.0
+            .write()
+            .ok()?
+            .completers
+            .insert(completer.command.clone(), completer)
+    }
+
+    /// Add `segment`, returning the segment of the same name it replaces
+    pub fn register_segment(&self, segment: PromptSegment) -> Option<PromptSegment> {
+        self.0
+            .write()
+            .ok()?
+            .segments
+            .insert(segment.name.clone(), segment)
+    }
+
+    /// Remove the commands, completers and prompt segments of `plugin`,
+    /// returning the names of its commands
+    pub fn unregister_plugin(&self, plugin: &str) -> Vec<String> {
+        let Ok(mut registered) = self.0.write() else {
+            return Vec::new();
+        };
+        let names: Vec<String> = registered
+            .commands
+            .values()
+            .filter(|command| command.plugin == plugin)
+            .map(|command| command.name.clone())
+            .collect();
+        for name in &names {
+            registered.commands.remove(name);
+        }
+        registered
+            .completers
+            .retain(|_, completer| completer.plugin != plugin);
+        registered
+            .segments
+            .retain(|_, segment| segment.plugin != plugin);
+        names
+    }
+
+    /// The command named `name`
+    pub fn get(&self, name: &str) -> Option<PluginCommand> {
+        self.0.read().ok()?.commands.get(name).cloned()
+    }
+
+    /// Whether a command named `name` is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.0
+            .read()
+            .map(|registered| registered.commands.contains_key(name))
+            .unwrap_or(false)
+    }
+
+    /// Registered commands, in name order
+    pub fn commands(&self) -> Vec<PluginCommand> {
+        self.0
+            .read()
+            .map(|registered| registered.commands.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Names of the registered commands, in order
+    pub fn names(&self) -> Vec<String> {
+        self.0
+            .read()
+            .map(|registered| registered.commands.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The completer for the arguments of `command`
+    pub fn completer(&self, command: &str) -> Option<PluginCompleter> {
+        self.0.read().ok()?.completers.get(command).cloned()
+    }
+
+    /// The prompt segment named `name`
+    pub fn segment(&self, name: &str) -> Option<PromptSegment> {
+        self.0.read().ok()?.segments.get(name).cloned()
+    }
+
+    /// Registered prompt segments, in name order
+    pub fn segments(&self) -> Vec<PromptSegment> {
+        self.0
+            .read()
+            .map(|registered| registered.segments.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for PluginCommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PluginCommandRegistry({:?})", self.names())
+    }
+}
@@ -15,13 +15,21 @@
 //!   fully-functional CUI fallback and as an embeddable engine surface.
 
 use crate::compat::Result;
-use crate::context::ShellContext;
+use crate::context::{PositionalParams, ShellArray, ShellContext, ShellOptions};
 use crate::error::{ErrorKind, ShellError, ShellResult};
-use crate::executor::{ExecutionResult, Executor};
+use crate::executor::{
+    Builtin, ExecutionResult, Executor, MenuRenderer, PostCommandHooks, StructuredBuiltin,
+    TableRenderer,
+};
+use crate::hooks::{HookEvent, HookRegistry};
+use crate::job::JobManager;
+use crate::plugin_commands::PluginCommandRegistry;
+use crate::trap::TrapTable;
 
 use std::io::IsTerminal;
 use std::io::{self, Write};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tokio::io::AsyncBufReadExt;
 
 /// Configuration for the shell
@@ -61,19 +69,78 @@ pub struct ShellState {
     pub exit_status: i32,
     /// Shell variables
     pub variables: std::collections::HashMap<String, String>,
+    /// Registered traps, carried across evaluations like the rest of the state
+    pub traps: TrapTable,
+    /// `set`/`shopt` options in effect
+    pub options: ShellOptions,
+    /// `$0` and the script arguments `$1..$N`
+    pub positional: PositionalParams,
+    /// Array variables
+    pub arrays: std::collections::HashMap<String, ShellArray>,
+    /// Job table, shared so jobs outlive a single evaluation
+    pub job_manager: Arc<Mutex<JobManager>>,
+    /// Controlling terminal once job control is enabled
+    pub terminal: Option<Arc<nxsh_hal::TerminalControl>>,
+    /// Command history, shared so `!!` and `fc` see earlier lines
+    pub history: Arc<Mutex<Vec<String>>>,
+    /// `pushd` directory stack below the working directory, most recent last
+    pub dir_stack: Arc<Mutex<Vec<std::path::PathBuf>>>,
+    /// Told how each command line ended and how long it ran
+    pub post_command_hooks: PostCommandHooks,
+    /// Commands of loaded plugins, consulted before `$PATH`
+    pub plugin_commands: PluginCommandRegistry,
+    /// Run on shell events such as `cwd_changed`
+    pub hooks: HookRegistry,
 }
 
 impl ShellState {
     /// Create new shell state with given configuration
     pub fn new(config: Config) -> ShellResult<Self> {
+        let mut options = ShellOptions::default();
+        for name in &config.shell_options {
+            if !options.set(name, true) {
+                return Err(ShellError::new(
+                    ErrorKind::RuntimeError(crate::error::RuntimeErrorKind::InvalidArgument),
+                    format!("{name}: invalid option name"),
+                ));
+            }
+        }
         Ok(Self {
             config,
             cwd: std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("/")),
             environment: std::env::vars().collect(),
             exit_status: 0,
             variables: std::collections::HashMap::new(),
+            traps: TrapTable::new(),
+            options,
+            positional: PositionalParams::default(),
+            arrays: std::collections::HashMap::new(),
+            job_manager: Arc::new(Mutex::new(JobManager::new())),
+            terminal: None,
+            history: Arc::new(Mutex::new(Vec::new())),
+            dir_stack: Arc::new(Mutex::new(Vec::new())),
+            post_command_hooks: PostCommandHooks::default(),
+            plugin_commands: PluginCommandRegistry::default(),
+            hooks: HookRegistry::default(),
         })
     }
+
+    /// Take the controlling terminal for job control when stdin is one, so
+    /// foreground jobs get Ctrl-C and Ctrl-Z and can be stopped and resumed
+    pub fn enable_job_control(&mut self) {
+        if self.terminal.is_none() {
+            self.terminal = acquire_terminal();
+        }
+    }
+}
+
+/// Controlling terminal for job control, or `None` when stdin is not a
+/// terminal or it cannot be taken over
+fn acquire_terminal() -> Option<Arc<nxsh_hal::TerminalControl>> {
+    nxsh_hal::TerminalControl::acquire()
+        .ok()
+        .flatten()
+        .map(Arc::new)
 }
 
 /// Public shell facade combining parsing and execution.
@@ -112,6 +179,32 @@ impl Shell {
         for (key, value) in state.environment {
             shell.context.set_var(key, value);
         }
+        if let Ok(mut traps) = shell.context.traps.write() {
+            *traps = state.traps;
+        }
+        if let Ok(mut positional) = shell.context.positional.write() {
+            *positional = state.positional;
+        }
+        if let Ok(mut arrays) = shell.context.arrays.write() {
+            *arrays = state.arrays;
+        }
+        shell.context.job_manager = state.job_manager;
+        shell.context.terminal = state.terminal;
+        shell.context.history = state.history;
+        shell.context.dir_stack = state.dir_stack;
+        shell.context.post_command_hooks = state.post_command_hooks;
+        shell.context.plugin_commands = state.plugin_commands;
+        shell.context.hooks = state.hooks;
+        if let Ok(mut options) = shell.context.options.write() {
+            // Control-flow and runtime bookkeeping stay as the fresh context set them
+            *options = ShellOptions {
+                break_requested: options.break_requested,
+                continue_requested: options.continue_requested,
+                continue_on_error: options.continue_on_error,
+                subshell_level: options.subshell_level,
+                ..state.options
+            };
+        }
         shell
     }
 
@@ -128,6 +221,25 @@ impl Shell {
         let variables = environment.clone();
         let cwd = self.context.cwd.clone();
         let exit_status = self.context.get_exit_status();
+        let traps = self
+            .context
+            .traps
+            .read()
+            .map(|t| t.clone())
+            .unwrap_or_default();
+        let options = self.context.get_all_options().unwrap_or_default();
+        let positional = self
+            .context
+            .positional
+            .read()
+            .map(|p| p.clone())
+            .unwrap_or_default();
+        let arrays = self
+            .context
+            .arrays
+            .read()
+            .map(|a| a.clone())
+            .unwrap_or_default();
 
         ShellState {
             config: Config::default(),
@@ -135,7 +247,80 @@ impl Shell {
             environment,
             exit_status,
             variables,
+            traps,
+            options,
+            positional,
+            arrays,
+            job_manager: self.context.job_manager(),
+            terminal: self.context.terminal.clone(),
+            history: Arc::clone(&self.context.history),
+            dir_stack: Arc::clone(&self.context.dir_stack),
+            post_command_hooks: self.context.post_command_hooks.clone(),
+            plugin_commands: self.context.plugin_commands.clone(),
+            hooks: self.context.hooks.clone(),
+        }
+    }
+
+    /// Register an additional builtin (e.g. context-aware commands from `nxsh_builtins`)
+    pub fn register_builtin(&mut self, builtin: std::sync::Arc<dyn Builtin>) {
+        self.executor.register_builtin(builtin);
+    }
+
+    /// Register a builtin that exchanges typed values inside pipelines
+    pub fn register_structured_builtin(&mut self, builtin: Arc<dyn StructuredBuiltin>) {
+        self.executor.register_structured_builtin(builtin);
+    }
+
+    /// Draw structured pipeline output on a terminal with `renderer`
+    pub fn set_table_renderer(&mut self, renderer: TableRenderer) {
+        self.executor.set_table_renderer(renderer);
+    }
+
+    /// Draw the menus and prompts of `select` loops with `renderer`
+    pub fn set_menu_renderer(&mut self, renderer: MenuRenderer) {
+        self.executor.set_menu_renderer(renderer);
+    }
+
+    /// Run the EXIT trap, if one is set. Call once when the shell terminates.
+    pub fn run_exit_trap(&mut self) -> ShellResult<ExecutionResult> {
+        Ok(self
+            .executor
+            .run_exit_trap(&mut self.context)?
+            .unwrap_or_else(|| ExecutionResult::success(self.context.get_exit_status())))
+    }
+
+    /// Run the hooks on `event`, returning their output
+    pub fn run_hooks(&mut self, event: &HookEvent) -> ExecutionResult {
+        self.executor.run_hooks(event, &mut self.context)
+    }
+
+    /// Get ready to show a prompt: report the background jobs that finished
+    /// with a `[n]+  Done ...` line each on stderr, which takes them out of
+    /// the job table, running the `job_finished` hooks for each, then run
+    /// the `prompt_render` hooks. Returns the lines and the hooks' output.
+    pub fn before_prompt(&mut self) -> ExecutionResult {
+        let job_manager = self.context.job_manager();
+        let finished = match job_manager.lock() {
+            Ok(mut manager) => manager.take_finished_jobs(),
+            Err(_) => Vec::new(),
+        };
+        let mut output = ExecutionResult::success(0);
+        for (job, notice) in finished {
+            output.stderr.push_str(&notice);
+            output.stderr.push('\n');
+            let event = HookEvent::JobFinished {
+                job: job.id,
+                exit_code: job.exit_code().unwrap_or(0),
+                command: job.description,
+            };
+            let hooks = self.run_hooks(&event);
+            output.stdout.push_str(&hooks.stdout);
+            output.stderr.push_str(&hooks.stderr);
         }
+        let hooks = self.run_hooks(&HookEvent::PromptRender);
+        output.stdout.push_str(&hooks.stdout);
+        output.stderr.push_str(&hooks.stderr);
+        output
     }
 
     /// Evaluate an AST node
@@ -179,6 +364,23 @@ impl Shell {
         self.executor.execute(&ast, &mut self.context)
     }
 
+    /// Prepare a line typed at the prompt: expand history references unless
+    /// `set +H` is in effect, then record the result in the history. An
+    /// expansion error is returned as its message and nothing is recorded.
+    pub fn accept_line(&mut self, line: &str) -> std::result::Result<String, String> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let line = if self.context.get_option("histexpand").unwrap_or(true) {
+            crate::history::expand(line, &self.context.get_history())?
+                .unwrap_or_else(|| line.to_string())
+        } else {
+            line.to_string()
+        };
+        if !line.trim().is_empty() {
+            self.context.add_history(line.clone());
+        }
+        Ok(line)
+    }
+
     /// Execute a whole script source (can contain multiple statements/lines).
     pub fn eval_program(&mut self, source: &str) -> ShellResult<ExecutionResult> {
         if source.trim().is_empty() {
@@ -193,6 +395,42 @@ impl Shell {
         self.executor.execute(&ast, &mut self.context)
     }
 
+    /// Execute a whole script with the MIR engine, running the program
+    /// `cache` holds for the same source or compiling and storing it there.
+    pub fn eval_program_cached(
+        &mut self,
+        source: &str,
+        cache: &crate::mir::cache::MirCache,
+    ) -> ShellResult<ExecutionResult> {
+        if source.trim().is_empty() {
+            return Ok(ExecutionResult::success(0));
+        }
+        let program = match cache.load(source) {
+            Some(program) => program,
+            None => {
+                let ast = self.parser.parse(source).map_err(|e| {
+                    ShellError::new(
+                        ErrorKind::ParseError(crate::error::ParseErrorKind::SyntaxError),
+                        e.to_string(),
+                    )
+                })?;
+                let program = crate::mir::cache::compile(&ast);
+                // A cache that cannot be written only costs the next run a compile
+                if let Err(e) = cache.store(source, &program) {
+                    tracing::debug!("MIR cache store failed: {e}");
+                }
+                program
+            }
+        };
+        self.executor
+            .execute_compiled_mir(&program, &mut self.context)
+    }
+
+    /// Whether commands run on the MIR engine (`NXSH_EXEC_STRATEGY=mir`)
+    pub fn uses_mir_engine(&self) -> bool {
+        self.executor.strategy() == crate::executor::ExecutionStrategy::MirEngine
+    }
+
     /// Execute a script file by path. The file is read as UTF-8 text.
     pub fn run_script_file<P: AsRef<Path>>(&mut self, path: P) -> ShellResult<ExecutionResult> {
         let content = std::fs::read_to_string(&path).map_err(|e| {
@@ -209,12 +447,21 @@ impl Shell {
     pub async fn run(&mut self) -> Result<()> {
         // TTY-aware prompt management. We avoid heavy line editing here.
         let is_tty = io::stdin().is_terminal();
+        if is_tty && self.context.terminal.is_none() {
+            self.context.terminal = acquire_terminal();
+        }
         let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
         let mut line = String::new();
 
         loop {
-            // Print prompt only for TTY sessions.
+            // Print prompt only for TTY sessions, after reporting background
+            // jobs that finished since the last one and running the hooks.
             if is_tty {
+                let result = self.before_prompt();
+                let _ = write!(self.context.stdout, "{}", result.stdout);
+                let _ = write!(self.context.stderr, "{}", result.stderr);
+                let _ = self.context.stdout.flush();
+                let _ = self.context.stderr.flush();
                 self.print_prompt()?;
             }
 
@@ -231,6 +478,22 @@ impl Shell {
                 break;
             }
 
+            let line = match self.accept_line(&line) {
+                Ok(accepted) => {
+                    // Show the command a history reference expanded to
+                    if accepted != line.trim_end_matches(['\n', '\r']) {
+                        let _ = writeln!(self.context.stdout, "{accepted}");
+                    }
+                    accepted
+                }
+                Err(message) => {
+                    let _ = writeln!(self.context.stderr, "nxsh: {message}");
+                    let _ = self.context.stderr.flush();
+                    self.context.set_exit_status(1);
+                    continue;
+                }
+            };
+
             match self.eval_line(&line) {
                 Ok(result) => {
                     // Write command output; in a full UI this is routed differently.
@@ -261,6 +524,18 @@ impl Shell {
             }
         }
 
+        match self.run_exit_trap() {
+            Ok(result) => {
+                let _ = write!(self.context.stdout, "{}", result.stdout);
+                let _ = write!(self.context.stderr, "{}", result.stderr);
+            }
+            Err(err) => {
+                let _ = writeln!(self.context.stderr, "nxsh: {err}");
+            }
+        }
+        let _ = self.context.stdout.flush();
+        let _ = self.context.stderr.flush();
+
         Ok(())
     }
 
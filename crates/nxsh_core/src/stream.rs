@@ -6,6 +6,9 @@
 use crate::error::{ErrorKind, ShellError, ShellResult};
 use std::collections::HashMap;
 use std::fmt;
+use std::fs::File;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 /// Stream type enumeration for different data formats
@@ -478,6 +481,96 @@ impl Stream {
     }
 }
 
+/// Byte-level sink so a stream can stand in for `ShellContext::stdout`
+/// (command substitution captures through this).
+impl std::io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Stream::write(self, StreamData::Bytes(buf.to_vec()))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::BrokenPipe, e.to_string()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where the standard output of a running command goes. Commands write
+/// straight to their target while they run; only [`OutputTarget::Capture`]
+/// collects the output, for command substitution.
+#[derive(Debug, Default)]
+pub enum OutputTarget {
+    /// The shell's own stdout, usually the terminal
+    #[default]
+    Inherit,
+    /// Collected and handed back as the command's output
+    Capture,
+    /// The write end of a pipe read by the next pipeline stage
+    Pipe(File),
+}
+
+impl OutputTarget {
+    /// Whether output reaches the shell's own stdout
+    pub fn is_inherit(&self) -> bool {
+        matches!(self, OutputTarget::Inherit)
+    }
+
+    /// Stdout for a child process writing to this target
+    pub fn stdio(&self) -> std::io::Result<Stdio> {
+        match self {
+            OutputTarget::Inherit => Ok(Stdio::inherit()),
+            OutputTarget::Capture => Ok(Stdio::piped()),
+            OutputTarget::Pipe(pipe) => pipe.try_clone().map(Stdio::from),
+        }
+    }
+}
+
+/// Write end of a pipe between pipeline stages that notes when its reader
+/// has gone, so a stage running in the shell can end the way a process
+/// killed by SIGPIPE does
+#[derive(Debug)]
+pub struct PipeWriter {
+    pipe: File,
+    broken: Arc<AtomicBool>,
+}
+
+impl PipeWriter {
+    /// Watch writes to `pipe`
+    pub fn new(pipe: File) -> Self {
+        Self {
+            pipe,
+            broken: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Another handle on the same pipe, sharing what it notes
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(Self {
+            pipe: self.pipe.try_clone()?,
+            broken: Arc::clone(&self.broken),
+        })
+    }
+
+    /// Whether a write through any handle found the reader gone
+    pub fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::Relaxed)
+    }
+}
+
+impl std::io::Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pipe.write(buf).inspect_err(|e| {
+            if e.kind() == std::io::ErrorKind::BrokenPipe {
+                self.broken.store(true, Ordering::Relaxed);
+            }
+        })
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.pipe.flush()
+    }
+}
+
 /// Stream conversion utilities
 pub struct StreamConverter;
 
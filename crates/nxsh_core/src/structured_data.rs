@@ -166,6 +166,104 @@ impl PipelineData {
         self
     }
 
+    /// Metadata key holding the preferred column order, comma separated
+    pub const COLUMNS: &'static str = "columns";
+
+    /// Record `columns` as the order to show table columns in
+    pub fn with_columns<S: AsRef<str>>(mut self, columns: &[S]) -> Self {
+        let columns: Vec<&str> = columns.iter().map(AsRef::as_ref).collect();
+        self.metadata
+            .insert(Self::COLUMNS.to_string(), columns.join(","));
+        self
+    }
+
+    /// Column names of a table or record value: the recorded order first,
+    /// then any other fields in name order
+    pub fn columns(&self) -> Vec<String> {
+        let mut present = std::collections::BTreeSet::new();
+        match &self.value {
+            StructuredValue::Table(rows) => {
+                for row in rows {
+                    present.extend(row.keys().cloned());
+                }
+            }
+            StructuredValue::Record(fields) => present.extend(fields.keys().cloned()),
+            _ => {}
+        }
+        let mut columns: Vec<String> = self
+            .metadata
+            .get(Self::COLUMNS)
+            .map(|order| {
+                order
+                    .split(',')
+                    .filter(|column| present.remove(*column))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        columns.extend(present);
+        columns
+    }
+
+    /// Whether the value is best shown as a table
+    pub fn is_tabular(&self) -> bool {
+        matches!(
+            self.value,
+            StructuredValue::Table(_) | StructuredValue::Record(_) | StructuredValue::List(_)
+        )
+    }
+
+    /// Headers and cell text of the value as a table. A record becomes one
+    /// row per field and a list one row per item.
+    pub fn cells(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        match &self.value {
+            StructuredValue::Table(rows) => {
+                let columns = self.columns();
+                let cells = rows
+                    .iter()
+                    .map(|row| {
+                        columns
+                            .iter()
+                            .map(|column| row.get(column).map(cell_text).unwrap_or_default())
+                            .collect()
+                    })
+                    .collect();
+                (columns, cells)
+            }
+            StructuredValue::Record(fields) => {
+                let cells = self
+                    .columns()
+                    .into_iter()
+                    .map(|name| {
+                        let value = fields.get(&name).map(cell_text).unwrap_or_default();
+                        vec![name, value]
+                    })
+                    .collect();
+                (vec!["name".to_string(), "value".to_string()], cells)
+            }
+            StructuredValue::List(items) => (
+                vec!["value".to_string()],
+                items.iter().map(|item| vec![cell_text(item)]).collect(),
+            ),
+            other => (vec!["value".to_string()], vec![vec![cell_text(other)]]),
+        }
+    }
+
+    /// Plain text for a file, pipe or command substitution: one line per
+    /// row or item with tab separated fields, and no header
+    pub fn to_text(&self) -> String {
+        let lines: Vec<String> = match &self.value {
+            StructuredValue::Nothing => Vec::new(),
+            StructuredValue::String(s) if s.is_empty() => Vec::new(),
+            StructuredValue::String(s) => vec![s.strip_suffix('\n').unwrap_or(s).to_string()],
+            StructuredValue::Table(_) | StructuredValue::Record(_) | StructuredValue::List(_) => {
+                self.cells().1.iter().map(|row| row.join("\t")).collect()
+            }
+            other => vec![other.to_string()],
+        };
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
     /// Convert table data to formatted string
     pub fn format_table(&self) -> String {
         match &self.value {
@@ -239,6 +337,14 @@ impl PipelineData {
     }
 }
 
+/// Text of one table cell; nothing shows as an empty cell
+fn cell_text(value: &StructuredValue) -> String {
+    match value {
+        StructuredValue::Nothing => String::new(),
+        other => other.to_string(),
+    }
+}
+
 /// Trait for commands that can process structured data
 pub trait StructuredCommand {
     /// Process pipeline data
@@ -352,6 +458,28 @@ mod tests {
         assert_eq!(data.metadata.get("source"), Some(&"test".to_string()));
     }
 
+    #[test]
+    fn test_table_cells_follow_column_order() {
+        let row = |name: &str, size: i64| {
+            HashMap::from([
+                (
+                    "name".to_string(),
+                    StructuredValue::String(name.to_string()),
+                ),
+                ("size".to_string(), StructuredValue::Int(size)),
+                ("extra".to_string(), StructuredValue::Nothing),
+            ])
+        };
+        let data = PipelineData::new(StructuredValue::Table(vec![row("a", 1), row("b", 22)]))
+            .with_columns(&["size", "name"]);
+
+        assert_eq!(data.columns(), vec!["size", "name", "extra"]);
+        let (headers, rows) = data.cells();
+        assert_eq!(headers, vec!["size", "name", "extra"]);
+        assert_eq!(rows[1], vec!["22", "b", ""]);
+        assert_eq!(data.to_text(), "1\ta\t\n22\tb\t\n");
+    }
+
     #[test]
     fn test_map_function() {
         let list = StructuredValue::List(vec![
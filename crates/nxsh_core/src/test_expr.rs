@@ -0,0 +1,412 @@
+//! Evaluation of extended test commands (`[[ ... ]]`)
+//!
+//! Operands are expanded without word splitting or pathname expansion. The
+//! right-hand side of `==`/`!=` is a glob pattern unless quoted, `=~` matches
+//! an extended regular expression and records captures in `BASH_REMATCH`,
+//! and numeric operators evaluate their operands as arithmetic expressions.
+
+use crate::context::{ShellArray, ShellContext};
+use crate::error::{ErrorKind, RuntimeErrorKind, ShellError, ShellResult};
+use nxsh_parser::ast::{AstNode, QuoteType, TestOperator, TestUnaryOperator, UnaryOperator};
+use std::path::Path;
+
+/// Expand `$name`, `${name}`, special parameters and a leading `~` in a word.
+///
+/// Backslash escapes are kept when `keep_escapes` is set so that pattern
+/// operands can still distinguish `\*` from `*`.
+pub fn expand_word(text: &str, ctx: &ShellContext, keep_escapes: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+
+    if chars.first() == Some(&'~') && (chars.len() == 1 || chars[1] == '/') {
+        if let Some(home) = ctx.get_var("HOME") {
+            out.push_str(&home);
+            i = 1;
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() {
+            if keep_escapes {
+                out.push(c);
+            }
+            out.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if c != '$' || i + 1 >= chars.len() {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+
+        let next = chars[i + 1];
+        if next == '{' {
+            if let Some(close) = chars[i + 2..].iter().position(|&x| x == '}') {
+                let name: String = chars[i + 2..i + 2 + close].iter().collect();
+                out.push_str(&lookup(&name, ctx));
+                i += close + 3;
+                continue;
+            }
+        } else if next == '?' || next == '$' || next == '#' || next.is_ascii_digit() {
+            out.push_str(&lookup(&next.to_string(), ctx));
+            i += 2;
+            continue;
+        } else if next.is_ascii_alphabetic() || next == '_' {
+            let start = i + 1;
+            let mut end = start;
+            while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+            let name: String = chars[start..end].iter().collect();
+            out.push_str(&lookup(&name, ctx));
+            i = end;
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+fn lookup(name: &str, ctx: &ShellContext) -> String {
+    match name {
+        "?" => ctx.get_exit_status().to_string(),
+        "$" => std::process::id().to_string(),
+        _ => ctx.get_var(name).unwrap_or_default(),
+    }
+}
+
+/// Expanded operand text plus whether it came from a quoted word
+fn operand(node: &AstNode, ctx: &ShellContext, keep_escapes: bool) -> (String, bool) {
+    match node {
+        AstNode::StringLiteral {
+            value,
+            quote_type: QuoteType::Single,
+        } => (value.to_string(), true),
+        AstNode::StringLiteral { value, .. } => (expand_word(value, ctx, false), true),
+        AstNode::Word(w) => (expand_word(w, ctx, keep_escapes), false),
+        AstNode::VariableExpansion { name, .. } => (lookup(name, ctx), true),
+        AstNode::NumberLiteral { value, .. } => (value.to_string(), false),
+        other => (format!("{other}"), false),
+    }
+}
+
+/// Match `text` against a shell glob pattern (`*`, `?`, `[...]`, `\x`)
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn class_matches(class: &[char], c: char) -> bool {
+        let (negate, body) = match class.first() {
+            Some('!') | Some('^') => (true, &class[1..]),
+            _ => (false, class),
+        };
+        let mut matched = false;
+        let mut i = 0;
+        while i < body.len() {
+            if i + 2 < body.len() && body[i + 1] == '-' {
+                if body[i] <= c && c <= body[i + 2] {
+                    matched = true;
+                }
+                i += 3;
+            } else {
+                if body[i] == c {
+                    matched = true;
+                }
+                i += 1;
+            }
+        }
+        matched != negate
+    }
+
+    fn rec(p: &[char], t: &[char]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some('*') => {
+                let rest = &p[1..];
+                (0..=t.len()).any(|k| rec(rest, &t[k..]))
+            }
+            Some('?') => !t.is_empty() && rec(&p[1..], &t[1..]),
+            Some('[') => {
+                // `]` directly after `[` or `[!` is literal
+                let start = if matches!(p.get(1), Some('!') | Some('^')) {
+                    2
+                } else {
+                    1
+                };
+                let close = p
+                    .iter()
+                    .skip(start + 1)
+                    .position(|&c| c == ']')
+                    .map(|k| k + start + 1);
+                match close {
+                    Some(end) => {
+                        !t.is_empty()
+                            && class_matches(&p[1..end], t[0])
+                            && rec(&p[end + 1..], &t[1..])
+                    }
+                    None => !t.is_empty() && t[0] == '[' && rec(&p[1..], &t[1..]),
+                }
+            }
+            Some('\\') if p.len() > 1 => !t.is_empty() && t[0] == p[1] && rec(&p[2..], &t[1..]),
+            Some(&c) => !t.is_empty() && t[0] == c && rec(&p[1..], &t[1..]),
+        }
+    }
+
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    rec(&p, &t)
+}
+
+fn regex_match(text: &str, pattern: &str, ctx: &ShellContext) -> ShellResult<bool> {
+    let re = regex::Regex::new(pattern).map_err(|e| {
+        ShellError::new(
+            ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
+            format!("[[: invalid regular expression `{pattern}': {e}"),
+        )
+    })?;
+    match re.captures(text) {
+        Some(caps) => {
+            let groups = caps
+                .iter()
+                .map(|group| group.map_or("", |m| m.as_str()).to_string());
+            ctx.set_array("BASH_REMATCH", ShellArray::from_values(groups));
+            Ok(true)
+        }
+        None => {
+            ctx.set_array("BASH_REMATCH", ShellArray::from_values(None));
+            Ok(false)
+        }
+    }
+}
+
+fn numeric(text: &str, ctx: &ShellContext) -> ShellResult<i64> {
+    crate::arithmetic::evaluate(text, ctx)
+}
+
+pub(crate) fn file_test(op: &TestUnaryOperator, path: &str) -> bool {
+    let p = Path::new(path);
+    let meta = || std::fs::metadata(p);
+    match op {
+        TestUnaryOperator::FileExists => p.exists(),
+        TestUnaryOperator::FileRegular => meta().map(|m| m.is_file()).unwrap_or(false),
+        TestUnaryOperator::FileDirectory => meta().map(|m| m.is_dir()).unwrap_or(false),
+        TestUnaryOperator::FileSymlink => std::fs::symlink_metadata(p)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false),
+        TestUnaryOperator::FileNonEmpty => meta().map(|m| m.len() > 0).unwrap_or(false),
+        TestUnaryOperator::FileModified => meta()
+            .and_then(|m| Ok(m.modified()? > m.accessed()?))
+            .unwrap_or(false),
+        TestUnaryOperator::FileReadable => access(p, AccessMode::Read),
+        TestUnaryOperator::FileWritable => access(p, AccessMode::Write),
+        TestUnaryOperator::FileExecutable => access(p, AccessMode::Execute),
+        #[cfg(unix)]
+        _ => unix_file_test(op, p),
+        #[cfg(not(unix))]
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn unix_file_test(op: &TestUnaryOperator, p: &Path) -> bool {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let Ok(m) = std::fs::metadata(p) else {
+        return false;
+    };
+    match op {
+        TestUnaryOperator::FileBlockDevice => m.file_type().is_block_device(),
+        TestUnaryOperator::FileCharDevice => m.file_type().is_char_device(),
+        TestUnaryOperator::FileFifo => m.file_type().is_fifo(),
+        TestUnaryOperator::FileSocket => m.file_type().is_socket(),
+        TestUnaryOperator::FileSticky => m.mode() & 0o1000 != 0,
+        TestUnaryOperator::FileSetgid => m.mode() & 0o2000 != 0,
+        TestUnaryOperator::FileSetuid => m.mode() & 0o4000 != 0,
+        // SAFETY: geteuid/getegid have no preconditions
+        TestUnaryOperator::FileOwned => m.uid() == unsafe { libc::geteuid() },
+        TestUnaryOperator::FileGroupOwned => m.gid() == unsafe { libc::getegid() },
+        _ => false,
+    }
+}
+
+enum AccessMode {
+    Read,
+    Write,
+    Execute,
+}
+
+#[cfg(unix)]
+fn access(p: &Path, mode: AccessMode) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(p.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let flag = match mode {
+        AccessMode::Read => libc::R_OK,
+        AccessMode::Write => libc::W_OK,
+        AccessMode::Execute => libc::X_OK,
+    };
+    // SAFETY: c_path is a valid NUL-terminated string for the duration of the call
+    unsafe { libc::access(c_path.as_ptr(), flag) == 0 }
+}
+
+#[cfg(not(unix))]
+fn access(p: &Path, mode: AccessMode) -> bool {
+    let Ok(m) = std::fs::metadata(p) else {
+        return false;
+    };
+    match mode {
+        AccessMode::Read => true,
+        AccessMode::Write => !m.permissions().readonly(),
+        AccessMode::Execute => {
+            m.is_dir()
+                || p.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+                    matches!(
+                        e.to_ascii_lowercase().as_str(),
+                        "exe" | "bat" | "cmd" | "com"
+                    )
+                })
+        }
+    }
+}
+
+pub(crate) fn is_tty(fd: &str) -> bool {
+    use std::io::IsTerminal;
+    match fd.trim() {
+        "0" => std::io::stdin().is_terminal(),
+        "1" => std::io::stdout().is_terminal(),
+        "2" => std::io::stderr().is_terminal(),
+        _ => false,
+    }
+}
+
+pub(crate) fn same_file(a: &str, b: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(x), Ok(y)) => x.dev() == y.dev() && x.ino() == y.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+            (Ok(x), Ok(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn modified(path: &str) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Evaluate a `[[ ]]` conditional expression tree
+pub fn evaluate(node: &AstNode, ctx: &ShellContext) -> ShellResult<bool> {
+    match node {
+        AstNode::TestExpression { condition, .. } => evaluate(condition, ctx),
+        AstNode::LogicalAnd { left, right } => Ok(evaluate(left, ctx)? && evaluate(right, ctx)?),
+        AstNode::LogicalOr { left, right } => Ok(evaluate(left, ctx)? || evaluate(right, ctx)?),
+        AstNode::UnaryExpression {
+            operator: UnaryOperator::LogicalNot,
+            operand,
+        } => Ok(!evaluate(operand, ctx)?),
+        AstNode::TestUnary { operator, operand } => {
+            let (value, _) = self::operand(operand, ctx, false);
+            Ok(match operator {
+                TestUnaryOperator::StringEmpty => value.is_empty(),
+                TestUnaryOperator::StringNonEmpty => !value.is_empty(),
+                TestUnaryOperator::VariableSet | TestUnaryOperator::VariableArray => {
+                    ctx.get_var(&value).is_some()
+                }
+                TestUnaryOperator::FileTty => is_tty(&value),
+                file_op => file_test(file_op, &value),
+            })
+        }
+        AstNode::TestBinary {
+            left,
+            operator,
+            right,
+        } => {
+            let (lhs, _) = operand(left, ctx, false);
+            match operator {
+                TestOperator::StringEqual | TestOperator::StringNotEqual => {
+                    let (rhs, quoted) = operand(right, ctx, true);
+                    let matched = if quoted {
+                        lhs == rhs
+                    } else {
+                        glob_match(&rhs, &lhs)
+                    };
+                    Ok(matched == (*operator == TestOperator::StringEqual))
+                }
+                TestOperator::StringMatch | TestOperator::StringNotMatch => {
+                    let (rhs, quoted) = operand(right, ctx, true);
+                    let pattern = if quoted { regex::escape(&rhs) } else { rhs };
+                    let matched = regex_match(&lhs, &pattern, ctx)?;
+                    Ok(matched == (*operator == TestOperator::StringMatch))
+                }
+                TestOperator::StringLess => Ok(lhs < operand(right, ctx, false).0),
+                TestOperator::StringGreater => Ok(lhs > operand(right, ctx, false).0),
+                TestOperator::NumericEqual
+                | TestOperator::NumericNotEqual
+                | TestOperator::NumericLess
+                | TestOperator::NumericLessEqual
+                | TestOperator::NumericGreater
+                | TestOperator::NumericGreaterEqual => {
+                    let a = numeric(&lhs, ctx)?;
+                    let b = numeric(&operand(right, ctx, false).0, ctx)?;
+                    Ok(match operator {
+                        TestOperator::NumericEqual => a == b,
+                        TestOperator::NumericNotEqual => a != b,
+                        TestOperator::NumericLess => a < b,
+                        TestOperator::NumericLessEqual => a <= b,
+                        TestOperator::NumericGreater => a > b,
+                        _ => a >= b,
+                    })
+                }
+                TestOperator::FileNewer | TestOperator::FileOlder => {
+                    let rhs = operand(right, ctx, false).0;
+                    let (a, b) = if *operator == TestOperator::FileNewer {
+                        (modified(&lhs), modified(&rhs))
+                    } else {
+                        (modified(&rhs), modified(&lhs))
+                    };
+                    Ok(match (a, b) {
+                        (Some(a), Some(b)) => a > b,
+                        (Some(_), None) => true,
+                        _ => false,
+                    })
+                }
+                TestOperator::FileSame => Ok(same_file(&lhs, &operand(right, ctx, false).0)),
+            }
+        }
+        other => {
+            // Any other node is treated as a plain word operand
+            Ok(!operand(other, ctx, false).0.is_empty())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("a*c", "abbbc"));
+        assert!(glob_match("file.[ch]", "file.h"));
+        assert!(!glob_match("file.[!ch]", "file.c"));
+        assert!(glob_match("?x", "ax"));
+        assert!(!glob_match("a\\*", "ab"));
+        assert!(glob_match("a\\*", "a*"));
+    }
+
+    #[test]
+    fn regex_populates_rematch() {
+        let ctx = ShellContext::new();
+        assert!(regex_match("v1.22", r"^v([0-9]+)\.([0-9]+)$", &ctx).unwrap());
+        assert_eq!(ctx.get_var("BASH_REMATCH").as_deref(), Some("v1.22"));
+        assert_eq!(ctx.get_var("BASH_REMATCH[2]").as_deref(), Some("22"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Trap registry: shell code attached to signals and pseudo-signals
+//!
+//! `trap` associates a command string with a condition. Real signals are
+//! delivered through `nxsh_hal::signal`; the pseudo-signals `EXIT`, `ERR`,
+//! `DEBUG` and `RETURN` are raised by the executor itself.
+
+use crate::error::{ErrorKind, RuntimeErrorKind, ShellError, ShellResult};
+use nxsh_hal::signal::{self, ShellSignal};
+use std::collections::BTreeMap;
+
+/// Something a trap can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TrapCondition {
+    /// Shell is exiting
+    Exit,
+    /// A simple command is about to run
+    Debug,
+    /// A command returned non-zero outside of a tested context
+    Err,
+    /// A function or sourced script returned
+    Return,
+    /// An OS signal arrived
+    Signal(ShellSignal),
+}
+
+impl TrapCondition {
+    /// Parse a condition as accepted by `trap`: `EXIT`, `0`, `ERR`, `INT`,
+    /// `SIGINT`, `2`, ...
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.to_ascii_uppercase().as_str() {
+            "EXIT" | "SIGEXIT" | "0" => return Some(TrapCondition::Exit),
+            "DEBUG" => return Some(TrapCondition::Debug),
+            "ERR" => return Some(TrapCondition::Err),
+            "RETURN" => return Some(TrapCondition::Return),
+            _ => {}
+        }
+        if let Ok(number) = spec.parse::<i32>() {
+            return ShellSignal::from_number(number).map(TrapCondition::Signal);
+        }
+        ShellSignal::from_name(spec).map(TrapCondition::Signal)
+    }
+
+    /// Name used when printing traps (`trap -p`)
+    pub fn name(&self) -> String {
+        match self {
+            TrapCondition::Exit => "EXIT".to_string(),
+            TrapCondition::Debug => "DEBUG".to_string(),
+            TrapCondition::Err => "ERR".to_string(),
+            TrapCondition::Return => "RETURN".to_string(),
+            TrapCondition::Signal(sig) => sig.to_string(),
+        }
+    }
+}
+
+/// Registered trap actions, keyed by condition.
+///
+/// An empty action string means "ignore" (`trap '' INT`), matching POSIX.
+#[derive(Debug, Clone, Default)]
+pub struct TrapTable {
+    handlers: BTreeMap<TrapCondition, String>,
+}
+
+impl TrapTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach `action` to `condition`, installing the OS handler for signals
+    pub fn set(&mut self, condition: TrapCondition, action: impl Into<String>) -> ShellResult<()> {
+        let action = action.into();
+        if let TrapCondition::Signal(sig) = condition {
+            let installed = if action.is_empty() {
+                signal::ignore(sig)
+            } else {
+                signal::catch(sig)
+            };
+            installed.map_err(|e| signal_error(sig, e))?;
+        }
+        self.handlers.insert(condition, action);
+        Ok(())
+    }
+
+    /// Remove the trap on `condition`, restoring default signal behaviour
+    pub fn reset(&mut self, condition: TrapCondition) -> ShellResult<()> {
+        if let TrapCondition::Signal(sig) = condition {
+            if self.handlers.contains_key(&condition) {
+                signal::restore_default(sig).map_err(|e| signal_error(sig, e))?;
+            }
+        }
+        self.handlers.remove(&condition);
+        Ok(())
+    }
+
+    /// Action registered for `condition`, if any (empty means ignored)
+    pub fn get(&self, condition: TrapCondition) -> Option<&str> {
+        self.handlers.get(&condition).map(String::as_str)
+    }
+
+    /// Action to run for `condition`; `None` when unset or ignored
+    pub fn action(&self, condition: TrapCondition) -> Option<&str> {
+        self.get(condition).filter(|a| !a.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Iterate registered traps in a stable order
+    pub fn iter(&self) -> impl Iterator<Item = (TrapCondition, &str)> {
+        self.handlers.iter().map(|(c, a)| (*c, a.as_str()))
+    }
+
+    /// Traps inherited by a subshell: only ignored signals survive
+    pub fn for_subshell(&self) -> Self {
+        Self {
+            handlers: self
+                .handlers
+                .iter()
+                .filter(|(c, a)| matches!(c, TrapCondition::Signal(_)) && a.is_empty())
+                .map(|(c, a)| (*c, a.clone()))
+                .collect(),
+        }
+    }
+}
+
+fn signal_error(sig: ShellSignal, err: nxsh_hal::HalError) -> ShellError {
+    ShellError::new(
+        ErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument),
+        format!("trap: cannot handle {sig}: {err}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pseudo_and_real_signals() {
+        assert_eq!(TrapCondition::parse("exit"), Some(TrapCondition::Exit));
+        assert_eq!(TrapCondition::parse("0"), Some(TrapCondition::Exit));
+        assert_eq!(TrapCondition::parse("ERR"), Some(TrapCondition::Err));
+        assert_eq!(
+            TrapCondition::parse("SIGINT"),
+            Some(TrapCondition::Signal(ShellSignal::Interrupt))
+        );
+        assert_eq!(
+            TrapCondition::parse("15"),
+            Some(TrapCondition::Signal(ShellSignal::Terminate))
+        );
+        assert_eq!(TrapCondition::parse("BOGUS"), None);
+    }
+
+    #[test]
+    fn empty_action_is_ignore() {
+        let mut table = TrapTable::new();
+        table.set(TrapCondition::Exit, "").unwrap();
+        assert_eq!(table.get(TrapCondition::Exit), Some(""));
+        assert_eq!(table.action(TrapCondition::Exit), None);
+        table.reset(TrapCondition::Exit).unwrap();
+        assert!(table.is_empty());
+    }
+}
@@ -45,7 +45,12 @@ fn test_simple_background_job() {
                 println!("Background job executed successfully");
                 println!("Output: {}", execution_result.stdout);
                 assert_eq!(execution_result.exit_code, 0);
-                assert!(execution_result.stdout.contains("Background job started"));
+                let job_manager = context.job_manager();
+                let jobs = job_manager
+                    .lock()
+                    .expect("Failed to lock job manager")
+                    .get_all_jobs();
+                assert_eq!(jobs.len(), 1);
             }
             Err(e) => {
                 eprintln!("Background job execution failed: {e:?}");
@@ -155,8 +160,8 @@ fn test_process_group_management() {
     let mut executor = create_test_executor();
     let mut context = create_test_context();
 
-    // Test process group management by starting a background job
-    let input = "echo 'process group test' &";
+    // Test process group management by starting an external background job
+    let input = "sleep 0 &";
     let parser = Parser::new();
 
     if let Ok(ast) = parser.parse(input) {
@@ -62,14 +62,14 @@ fn brace_escape_and_empty() {
     // Expect escaped braces to stay literal; empty element produces empty string between commas
     let out1 = eval(&["\\{a,b\\}"]); // parser would yield literal backslashes beforehand; here we assert passthrough
     assert_eq!(out1, vec!["\\{a,b\\}".to_string()]);
-    let out2 = eval(&["{x,,y}"]); // our brace expansion currently handled inside executor path, evaluate_args_for_test does not expand
+    let out2 = eval(&["{x,,y}"]); // our brace expansion currently handled inside executor path, evaluate_args does not expand
                                   // For now ensure raw form (acts as regression guard that we are not accidentally splitting here)
     assert_eq!(out2, vec!["{x,,y}".to_string()]);
 }
 
 #[test]
 fn extglob_subset_does_not_panic() {
-    // We cannot easily trigger glob expansion via evaluate_args_for_test (brace/glob run in execute_command path),
+    // We cannot easily trigger glob expansion via evaluate_args (brace/glob run in execute_command path),
     // so this is a placeholder ensuring construction of pattern strings is stable.
     let patterns = [
         "*(Cargo|README)*",
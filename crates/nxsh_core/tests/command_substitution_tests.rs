@@ -0,0 +1,52 @@
+//! Command substitution: output capture, nesting, backquotes and quoting
+mod common;
+use common::shell;
+
+#[test]
+fn captures_inner_output() {
+    let mut sh = shell();
+    let res = sh.eval_program("echo $(echo hi)").unwrap();
+    assert_eq!(res.stdout, "hi\n");
+}
+
+#[test]
+fn nested_substitutions() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("echo $(echo $(echo deep) \"$(echo in)\")")
+        .unwrap();
+    assert_eq!(res.stdout, "deep in\n");
+}
+
+#[test]
+fn backquotes_nest_with_escapes() {
+    let mut sh = shell();
+    let res = sh.eval_program("echo `echo \\`echo inner\\``").unwrap();
+    assert_eq!(res.stdout, "inner\n");
+}
+
+#[test]
+fn trailing_newlines_are_stripped() {
+    let mut sh = shell();
+    sh.eval_program("x=$(echo a; echo; echo)").unwrap();
+    assert_eq!(sh.context().get_var("x").as_deref(), Some("a"));
+}
+
+#[test]
+fn assignment_keeps_output_unsplit_and_reports_status() {
+    let mut sh = shell();
+    let res = sh.eval_program("v=\"<$(echo a   b)>\"").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(sh.context().get_var("v").as_deref(), Some("<a b>"));
+    let res = sh.eval_program("w=$(false)").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
+
+#[test]
+fn quoted_substitution_is_one_field() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("a=($(echo x y z))\nb=(\"$(echo x y z)\")\necho ${#a[@]} ${#b[@]}")
+        .unwrap();
+    assert_eq!(res.stdout, "3 1\n");
+}
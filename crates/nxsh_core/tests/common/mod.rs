@@ -0,0 +1,29 @@
+//! Shells and scripts for the integration tests
+
+#![allow(dead_code)]
+
+use nxsh_core::Shell;
+
+/// A shell without the time limit `NXSH_TIMEOUT_MS` may set
+pub fn shell() -> Shell {
+    let mut shell = Shell::new();
+    shell.context_mut().clear_global_timeout();
+    shell
+}
+
+/// [`shell`] working in `dir`
+pub fn shell_in(dir: &tempfile::TempDir) -> Shell {
+    let mut shell = shell();
+    shell.context_mut().cwd = dir.path().to_path_buf();
+    shell
+}
+
+/// An executable `/bin/sh` script called `name` in `dir` running `body`
+#[cfg(unix)]
+pub fn script(dir: &tempfile::TempDir, name: &str, body: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.path().join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
@@ -0,0 +1,58 @@
+//! `exec` rebinds the shell's descriptors or hands the process to a command
+mod common;
+use common::shell_in;
+use std::fs;
+
+#[cfg(unix)]
+#[test]
+fn redirection_only_exec_rebinds_descriptors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(&dir);
+
+    // A high descriptor keeps clear of the test harness's own files
+    let res = sh
+        .eval_program("exec 19>log.txt\nsh -c 'echo one >&19'\nsh -c 'echo two >&19'")
+        .unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let res = sh
+        .eval_program("exec 19>&-\nsh -c 'echo three >&19'")
+        .unwrap();
+    assert_ne!(res.exit_code, 0);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("log.txt")).unwrap(),
+        "one\ntwo\n"
+    );
+
+    let res = sh.eval_program("exec 19<missing.txt").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("missing.txt"));
+}
+
+#[test]
+fn exec_in_a_substitution_runs_the_command_there() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(&dir);
+
+    let res = sh.eval_program("echo $(exec echo inner) outer").unwrap();
+    assert_eq!(res.stdout, "inner outer\n");
+    let res = sh.eval_program("exec").unwrap();
+    assert_eq!(res.exit_code, 0);
+    let res = sh.eval_program("exec -x true").unwrap();
+    assert_eq!(res.exit_code, 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_options_apply_in_a_substitution() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sh = shell_in(&dir);
+
+    common::script(&dir, "show", "echo $0 ${HOME:-cleared}\n");
+
+    let res = sh.eval_program("echo $(exec -c ./show)").unwrap();
+    assert_eq!(res.stdout, "./show cleared\n");
+    let res = sh
+        .eval_program("echo $(exec -la name sh -c 'echo $0')")
+        .unwrap();
+    assert_eq!(res.stdout, "-name\n");
+}
@@ -170,3 +170,203 @@ fn test_context_integration() {
     // Verify context is properly initialized
     assert!(context.cwd.exists(), "Current directory should exist");
 }
+
+#[test]
+fn test_arithmetic_for_loop_updates_variable() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("for ((i=0; i<5; i++)); do true; done")
+        .expect("Failed to parse arithmetic for loop");
+
+    let result = executor.execute(&ast, &mut context).expect("loop failed");
+    assert_eq!(result.exit_code, 0);
+    assert_eq!(context.get_var("i").as_deref(), Some("5"));
+}
+
+#[test]
+fn test_until_loop_stops_when_condition_succeeds() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("until true; do false; done")
+        .expect("Failed to parse until loop");
+
+    // Condition succeeds immediately, so the body never runs
+    let result = executor.execute(&ast, &mut context).expect("loop failed");
+    assert_eq!(result.exit_code, 0);
+}
+
+#[test]
+fn test_until_loop_runs_until_condition_succeeds() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("until [[ $n == xxx ]]; do n=${n}x; done")
+        .expect("Failed to parse until loop");
+
+    let result = executor.execute(&ast, &mut context).expect("loop failed");
+    assert_eq!(result.exit_code, 0);
+    assert_eq!(context.get_var("n").as_deref(), Some("xxx"));
+}
+
+#[test]
+fn test_arithmetic_for_loop_with_parenthesised_step() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("for ((i=0; i<5; i+=(2))); do true; done")
+        .expect("Failed to parse arithmetic for loop");
+
+    executor.execute(&ast, &mut context).expect("loop failed");
+    assert_eq!(context.get_var("i").as_deref(), Some("6"));
+}
+
+#[test]
+fn test_select_reads_choice_into_variable() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+    context.stdin = Box::new(std::io::Cursor::new(b"2\n".to_vec()));
+    context.stderr = Box::new(std::io::sink());
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("select fruit in apple banana cherry; do true; done")
+        .expect("Failed to parse select");
+
+    // The loop ends at EOF after the single reply has been handled
+    executor.execute(&ast, &mut context).expect("select failed");
+    assert_eq!(context.get_var("fruit").as_deref(), Some("banana"));
+    assert_eq!(context.get_var("REPLY").as_deref(), Some("2"));
+}
+
+#[test]
+fn test_select_draws_menu_with_renderer() {
+    use std::sync::Mutex;
+
+    static SHOWN: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    fn record(items: &[String], prompt: &str, _columns: usize) -> String {
+        SHOWN.lock().unwrap().push(items.len());
+        prompt.to_string()
+    }
+
+    let mut executor = create_test_executor();
+    executor.set_menu_renderer(record);
+    let mut context = create_test_context();
+    context.stdin = Box::new(std::io::Cursor::new(b"1\n".to_vec()));
+    context.stderr = Box::new(std::io::sink());
+
+    let parser = Parser::new();
+    let ast = parser
+        .parse("select fruit in apple banana cherry; do true; done")
+        .expect("Failed to parse select");
+
+    // The menu is drawn once, then only the prompt before the next reply
+    executor.execute(&ast, &mut context).expect("select failed");
+    assert_eq!(*SHOWN.lock().unwrap(), vec![3, 0]);
+}
+
+#[test]
+fn test_extended_test_pattern_and_regex() {
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+    context.set_var("ver", "v1.42");
+
+    let parser = Parser::new();
+    let glob = parser.parse("[[ $ver == v1.* ]]").unwrap();
+    assert_eq!(executor.execute(&glob, &mut context).unwrap().exit_code, 0);
+
+    let quoted = parser.parse("[[ $ver == \"v1.*\" ]]").unwrap();
+    assert_eq!(
+        executor.execute(&quoted, &mut context).unwrap().exit_code,
+        1
+    );
+
+    let regex = parser
+        .parse("[[ $ver =~ ^v([0-9]+)\\.([0-9]+)$ ]]")
+        .unwrap();
+    assert_eq!(executor.execute(&regex, &mut context).unwrap().exit_code, 0);
+    assert_eq!(context.get_var("BASH_REMATCH[2]").as_deref(), Some("42"));
+}
+
+#[test]
+fn test_post_command_hooks_report_each_command_line() {
+    use nxsh_core::CommandReport;
+    use std::sync::{Arc, Mutex};
+
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+    let reports: Arc<Mutex<Vec<CommandReport>>> = Arc::default();
+    let seen = Arc::clone(&reports);
+    context.post_command_hooks.add(Arc::new(move |report| {
+        seen.lock().unwrap().push(report.clone())
+    }));
+
+    let parser = Parser::new();
+    let failing = parser.parse("[[ a == b ]]").unwrap();
+    executor.execute_ast(&failing, &mut context).unwrap();
+    let passing = parser.parse("[[ a == a ]]").unwrap();
+    executor.execute_ast(&passing, &mut context).unwrap();
+
+    let reports = reports.lock().unwrap();
+    let codes: Vec<i32> = reports.iter().map(|r| r.exit_code).collect();
+    assert_eq!(codes, vec![1, 0]);
+}
+
+#[test]
+fn test_hooks_fire_around_command_lines() {
+    use nxsh_core::{ExecutionResult, HookEvent, HookKind};
+    use std::sync::{Arc, Mutex};
+
+    let mut executor = create_test_executor();
+    let mut context = create_test_context();
+    let events: Arc<Mutex<Vec<HookEvent>>> = Arc::default();
+    for kind in HookKind::ALL {
+        let seen = Arc::clone(&events);
+        context.hooks.add_callback(
+            kind,
+            "test",
+            "record",
+            Arc::new(move |event: &HookEvent, _: &mut ShellContext| {
+                seen.lock().unwrap().push(event.clone());
+                Ok(ExecutionResult::success(0).with_output(b"hooked\n".to_vec()))
+            }),
+        );
+    }
+
+    let parser = Parser::new();
+    let failing = parser.parse("[[ a == b ]]").unwrap();
+    let result = executor.execute_ast(&failing, &mut context).unwrap();
+    assert_eq!(result.exit_code, 1);
+    assert_eq!(result.stdout, "hooked\nhooked\n");
+    let prompt = executor.run_hooks(&HookEvent::PromptRender, &mut context);
+    assert_eq!(prompt.stdout, "hooked\n");
+
+    let kinds: Vec<HookKind> = events.lock().unwrap().iter().map(HookEvent::kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            HookKind::PreCommand,
+            HookKind::PostCommand,
+            HookKind::PromptRender
+        ]
+    );
+    assert!(matches!(
+        events.lock().unwrap()[1],
+        HookEvent::PostCommand { exit_code: 1, .. }
+    ));
+
+    // A plugin's hooks go when it does
+    assert_eq!(context.hooks.remove_owner("test"), HookKind::ALL.len());
+    let passing = parser.parse("[[ a == a ]]").unwrap();
+    let result = executor.execute_ast(&passing, &mut context).unwrap();
+    assert_eq!(result.stdout, "");
+}
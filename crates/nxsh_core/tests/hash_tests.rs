@@ -0,0 +1,53 @@
+//! External commands are found through `$PATH` and remembered by `hash`
+mod common;
+use common::shell;
+
+#[cfg(unix)]
+#[test]
+fn runs_are_counted_and_rehash_forgets() {
+    let mut sh = shell();
+    let res = sh.eval_program("true\ntrue\nhash").unwrap();
+    assert!(res.stdout.starts_with("hits\tcommand\n"), "{}", res.stdout);
+    assert!(res.stdout.contains("   2\t"), "{}", res.stdout);
+
+    let res = sh.eval_program("hash -t sh").unwrap();
+    assert!(res.stdout.trim_end().ends_with("/sh"));
+
+    let res = sh.eval_program("hash -r\nhash").unwrap();
+    assert_eq!(res.stdout, "hash: hash table empty\n");
+    let res = sh.eval_program("hash -d sh").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn missing_commands_and_signals_report_status() {
+    let mut sh = shell();
+    let res = sh.eval_program("nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert!(res.stderr.contains("command not found"));
+
+    let res = sh.eval_program("sh -c 'kill -KILL $$'").unwrap();
+    assert_eq!(res.exit_code, 137);
+    assert!(res.stderr.contains("Killed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn missing_commands_suggest_close_names() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("nxsh_greeting() { echo hi; }\nnxsh_greetign")
+        .unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert_eq!(
+        res.stderr,
+        "nxsh: command not found: nxsh_greetign — did you mean: nxsh_greeting?\n"
+    );
+
+    let res = sh.eval_program("nxsh-no-such-command").unwrap();
+    assert_eq!(
+        res.stderr,
+        "nxsh: command not found: nxsh-no-such-command\n"
+    );
+}
@@ -0,0 +1,67 @@
+//! History expansion at the prompt and the `fc` builtin
+mod common;
+use nxsh_core::Shell;
+
+fn shell_with_history(lines: &[&str]) -> Shell {
+    let shell = common::shell();
+    for line in lines {
+        shell.context().add_history(line.to_string());
+    }
+    shell
+}
+
+#[test]
+fn accepted_lines_expand_and_are_recorded() {
+    let mut sh = shell_with_history(&["echo one two"]);
+    assert_eq!(sh.accept_line("printf %s !$\n").unwrap(), "printf %s two");
+    assert_eq!(sh.accept_line("!!").unwrap(), "printf %s two");
+    assert_eq!(
+        sh.accept_line("!nope"),
+        Err("!nope: event not found".into())
+    );
+    assert_eq!(sh.context().get_history().len(), 3);
+
+    // `set +H` turns expansion off
+    sh.context().set_option("histexpand", false).unwrap();
+    assert_eq!(sh.accept_line("echo !!").unwrap(), "echo !!");
+}
+
+#[test]
+fn fc_lists_ranges() {
+    let mut sh = shell_with_history(&["echo one", "echo two", "echo three"]);
+    let res = sh.eval_program("fc -l").unwrap();
+    assert_eq!(res.stdout, "1\t echo one\n2\t echo two\n3\t echo three\n");
+
+    let res = sh.eval_program("fc -ln -1").unwrap();
+    assert_eq!(res.stdout, "\t echo three\n");
+    let res = sh.eval_program("fc -lr 1 2").unwrap();
+    assert_eq!(res.stdout, "2\t echo two\n1\t echo one\n");
+
+    let res = sh.eval_program("fc -l 9").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("out of range"), "{}", res.stderr);
+}
+
+#[test]
+fn fc_s_reruns_with_substitution() {
+    let mut sh = shell_with_history(&["echo two", "fc -s two=three"]);
+    let res = sh.eval_program("fc -s two=three").unwrap();
+    assert_eq!(res.stdout, "echo three\nthree\n");
+    assert_eq!(
+        sh.context().get_history().last().map(String::as_str),
+        Some("echo three")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn fc_runs_the_edited_commands() {
+    // `true` leaves the file as written, so the last command runs again
+    let mut sh = shell_with_history(&["echo again"]);
+    let res = sh.eval_program("fc -e true").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "echo again\nagain\n");
+
+    let res = sh.eval_program("fc -e false").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
@@ -0,0 +1,121 @@
+//! Background jobs through the shared job manager: `$!`, `wait`, `jobs`,
+//! `disown`, `kill %n` and completion notices
+mod common;
+use common::shell;
+use nxsh_core::Shell;
+
+#[test]
+fn wait_returns_the_jobs_status() {
+    let mut sh = shell();
+    let res = sh.eval_program("false &\nwait $!").unwrap();
+    assert_eq!(res.exit_code, 1);
+    let res = sh.eval_program("true &\nwait %1").unwrap();
+    assert_eq!(res.exit_code, 0);
+    let res = sh.eval_program("wait %7").unwrap();
+    assert_eq!(res.exit_code, 127);
+}
+
+#[test]
+fn background_pipeline_is_one_job() {
+    let mut sh = shell();
+    sh.eval_program("sleep 0.1 | cat &").unwrap();
+    let last = sh.context().last_background_pid.expect("$! is set");
+    let job_manager = sh.context().job_manager();
+    let jobs = job_manager.lock().unwrap().get_all_jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].description, "sleep 0.1 | cat");
+    assert_eq!(jobs[0].processes.len(), 2);
+    assert_eq!(jobs[0].processes[1].pid, last);
+    assert_eq!(jobs[0].processes[1].pgid, jobs[0].processes[0].pid);
+
+    let res = sh.eval_program("wait").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(job_manager.lock().unwrap().get_all_jobs().is_empty());
+}
+
+#[test]
+fn builtins_run_as_finished_jobs() {
+    let mut sh = shell();
+    let res = sh.eval_program("echo hi &").unwrap();
+    assert_eq!(res.stdout, "hi\n");
+    assert_eq!(res.exit_code, 0);
+    let notices = sh
+        .context()
+        .job_manager()
+        .lock()
+        .unwrap()
+        .take_completion_notices();
+    assert_eq!(notices, vec![format!("[1]+  {:<24}echo hi", "Done")]);
+}
+
+#[test]
+fn completion_is_reported_once() {
+    let mut sh = shell();
+    sh.eval_program("false &").unwrap();
+    let job_manager = sh.context().job_manager();
+    job_manager.lock().unwrap().wait_for_job(1).unwrap();
+    let notices = job_manager.lock().unwrap().take_completion_notices();
+    assert_eq!(notices, vec![format!("[1]+  {:<24}false", "Exit 1")]);
+    assert!(job_manager
+        .lock()
+        .unwrap()
+        .take_completion_notices()
+        .is_empty());
+}
+
+#[test]
+fn jobs_kill_and_disown() {
+    let mut sh = shell();
+    let res = sh.eval_program("sleep 5 &\nsleep 5 &\njobs").unwrap();
+    assert_eq!(
+        res.stdout,
+        format!(
+            "[1]-  {0:<24}sleep 5 &\n[2]+  {0:<24}sleep 5 &\n",
+            "Running"
+        )
+    );
+    let second = sh.context().last_background_pid.unwrap();
+
+    let res = sh.eval_program("kill %1\nwait %1").unwrap();
+    assert_eq!(res.exit_code, 128 + 15);
+
+    let res = sh.eval_program("disown\njobs %2").unwrap();
+    assert_eq!(res.exit_code, 1);
+    let res = sh.eval_program("disown").unwrap();
+    assert_eq!(res.exit_code, 1);
+
+    // The disowned process is still running; clean it up by pid
+    let res = sh.eval_program(&format!("kill {second}")).unwrap();
+    assert_eq!(res.exit_code, 0);
+}
+
+/// Poll the job table until job 1 reaches `label` (monitors run on threads)
+#[cfg(unix)]
+fn wait_for_state(sh: &Shell, label: &str) {
+    let job_manager = sh.context().job_manager();
+    for _ in 0..200 {
+        let job = job_manager.lock().unwrap().get_job(1).unwrap();
+        if job.is_some_and(|job| job.state_label() == label) {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("job 1 never became {label}");
+}
+
+#[cfg(unix)]
+#[test]
+fn stopped_jobs_resume_with_bg() {
+    let mut sh = shell();
+    sh.eval_program("sleep 5 &\nkill -STOP %1").unwrap();
+    wait_for_state(&sh, "Stopped");
+    let res = sh.eval_program("jobs").unwrap();
+    assert_eq!(res.stdout, format!("[1]+  {:<24}sleep 5\n", "Stopped"));
+
+    let res = sh.eval_program("bg").unwrap();
+    assert_eq!(res.stdout, "[1]+ sleep 5 &\n");
+    wait_for_state(&sh, "Running");
+
+    let res = sh.eval_program("kill %1\nwait %1").unwrap();
+    assert_eq!(res.exit_code, 128 + 15);
+}
@@ -0,0 +1,88 @@
+//! `a &&& b` runs its members side by side and waits for all of them
+#![cfg(unix)]
+mod common;
+use common::{script, shell};
+use std::time::{Duration, Instant};
+
+/// A job that sleeps for its first argument, prints it and exits with its
+/// second
+fn job(dir: &tempfile::TempDir) -> String {
+    script(dir, "job", "sleep $1\necho $1\nexit $2\n")
+        .display()
+        .to_string()
+}
+
+/// A job that sleeps for its first argument and exits with its second
+fn quiet_job(dir: &tempfile::TempDir) -> String {
+    script(dir, "quiet", "sleep $1\nexit $2\n")
+        .display()
+        .to_string()
+}
+
+#[test]
+fn status_is_the_rightmost_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = quiet_job(&dir);
+    let mut sh = shell();
+    let res = sh.eval_program("true &&& false &&& true").unwrap();
+    assert_eq!(res.exit_code, 1);
+    let res = sh
+        .eval_program(&format!("{job} 0 3 &&& {job} 0 2 &&& true"))
+        .unwrap();
+    assert_eq!(res.exit_code, 2);
+    let res = sh.eval_program("true &&& true").unwrap();
+    assert_eq!(res.exit_code, 0);
+}
+
+#[test]
+fn members_run_at_the_same_time() {
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh
+        .eval_program("sleep 0.4 &&& sleep 0.4 &&& sleep 0.4")
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(started.elapsed() < Duration::from_millis(1000));
+
+    let started = Instant::now();
+    let res = sh
+        .eval_program("nap() { sleep 0.4; }; nap &&& nap &&& sleep 0.4")
+        .unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(started.elapsed() < Duration::from_millis(1000));
+}
+
+#[test]
+fn output_comes_back_in_member_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir);
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("out=$({job} 0.2 0 &&& echo b); echo $out"))
+        .unwrap();
+    assert_eq!(res.stdout, "0.2\nb\n");
+    let res = sh
+        .eval_program("out=$( (echo a &&& echo b) | sort -r); echo $out")
+        .unwrap();
+    assert_eq!(res.stdout, "b\na\n");
+}
+
+#[test]
+fn members_keep_their_assignments_to_themselves() {
+    let mut sh = shell();
+    let res = sh.eval_program("x=1; x=2 &&& true; echo $x").unwrap();
+    assert_eq!(res.stdout, "1\n");
+}
+
+#[test]
+fn backgrounded_group_is_one_job() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = quiet_job(&dir);
+    let mut sh = shell();
+    sh.eval_program(&format!("{job} 0 3 &&& {job} 0.2 0 &"))
+        .unwrap();
+    let res = sh.eval_program("jobs").unwrap();
+    assert_eq!(res.stdout.lines().count(), 1, "{}", res.stdout);
+    let res = sh.eval_program("wait %1").unwrap();
+    assert_eq!(res.exit_code, 3);
+}
@@ -0,0 +1,55 @@
+//! `kill` signal specifications: names with and without `SIG`, numbers,
+//! `-s`, `-n` and `-l`
+mod common;
+use common::shell;
+
+#[test]
+fn lists_and_translates_signals() {
+    let mut sh = shell();
+    let res = sh.eval_program("kill -l").unwrap();
+    assert!(res.stdout.starts_with(" 1) SIGHUP\n 2) SIGINT\n"));
+    assert!(res.stdout.contains(" 9) SIGKILL\n"));
+
+    let res = sh.eval_program("kill -l 9 sigterm 130").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "KILL\n15\nINT\n");
+
+    let res = sh.eval_program("kill -l BOGUS").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "kill: BOGUS: invalid signal specification\n");
+}
+
+#[test]
+fn rejects_unknown_signals() {
+    let mut sh = shell();
+    let res = sh.eval_program("kill -s BOGUS 1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "kill: BOGUS: invalid signal specification\n");
+
+    let res = sh.eval_program("kill -99 1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(res.stderr, "kill: 99: invalid signal specification\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn sends_signals_by_name_and_number() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("sleep 5 &\nkill -SIGKILL $!\nwait $!")
+        .unwrap();
+    assert_eq!(res.exit_code, 128 + 9);
+
+    let res = sh
+        .eval_program("sleep 5 &\nkill -s hup $!\nwait $!")
+        .unwrap();
+    assert_eq!(res.exit_code, 128 + 1);
+
+    let res = sh
+        .eval_program("sleep 5 &\nkill -n 15 $!\nwait $!")
+        .unwrap();
+    assert_eq!(res.exit_code, 128 + 15);
+
+    let res = sh.eval_program("sleep 5 &\nkill -9 $!\nwait $!").unwrap();
+    assert_eq!(res.exit_code, 128 + 9);
+}
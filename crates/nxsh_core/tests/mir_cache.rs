@@ -0,0 +1,120 @@
+use nxsh_core::mir::cache::{compile, MirCache};
+use nxsh_core::mir::{MirExecutor, MirValue};
+use nxsh_core::Shell;
+use nxsh_parser::ast::{AstNode, BinaryOperator, NumberType};
+use std::time::{Duration, SystemTime};
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+/// `return <left> + <right>`
+fn sum<'a>(left: &'a str, right: &'a str) -> AstNode<'a> {
+    AstNode::Program(vec![AstNode::Return(Some(Box::new(
+        AstNode::BinaryExpression {
+            left: Box::new(num(left)),
+            operator: BinaryOperator::Add,
+            right: Box::new(num(right)),
+        },
+    )))])
+}
+
+fn set_last_used(path: &std::path::Path, ago: Duration) {
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now() - ago))
+        .unwrap();
+}
+
+#[test]
+fn stored_program_loads_and_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let entry = cache
+        .store("return 1 + 2", &compile(&sum("1", "2")))
+        .unwrap();
+    assert!(entry.starts_with(dir.path()));
+    assert!(entry.exists());
+
+    let program = cache.load("return 1 + 2").expect("cached program");
+    let result = MirExecutor::new().execute_main(&program).unwrap();
+    assert_eq!(result, MirValue::Integer(3));
+}
+
+#[test]
+fn edited_source_misses_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    cache
+        .store("return 1 + 2", &compile(&sum("1", "2")))
+        .unwrap();
+    assert_ne!(
+        cache.entry_path("return 1 + 2"),
+        cache.entry_path("return 1 + 3")
+    );
+    assert!(cache.load("return 1 + 3").is_none());
+}
+
+#[test]
+fn damaged_entry_is_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let entry = cache
+        .store("return 1 + 2", &compile(&sum("1", "2")))
+        .unwrap();
+    let mut bytes = std::fs::read(&entry).unwrap();
+    bytes.truncate(bytes.len() / 2);
+    std::fs::write(&entry, bytes).unwrap();
+
+    assert!(cache.load("return 1 + 2").is_none());
+    assert!(!entry.exists());
+}
+
+#[test]
+fn eviction_drops_least_recently_used_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let old = cache.store("old", &compile(&sum("1", "2"))).unwrap();
+    let new = cache.store("new", &compile(&sum("3", "4"))).unwrap();
+    set_last_used(&old, Duration::from_secs(60 * 60));
+
+    let limit = std::fs::metadata(&new).unwrap().len();
+    let removed = MirCache::new(dir.path())
+        .with_max_bytes(limit)
+        .evict()
+        .unwrap();
+    assert_eq!(removed, 1);
+    assert!(!old.exists());
+    assert!(new.exists());
+}
+
+#[test]
+fn eviction_drops_expired_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path()).with_max_age(Duration::from_secs(60 * 60));
+    let stale = cache.store("stale", &compile(&sum("1", "2"))).unwrap();
+    set_last_used(&stale, Duration::from_secs(2 * 60 * 60));
+
+    let fresh = cache.store("fresh", &compile(&sum("3", "4"))).unwrap();
+    assert!(!stale.exists());
+    assert!(fresh.exists());
+}
+
+#[test]
+fn shell_compiles_once_and_reuses_the_cached_program() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let source = "x=1\n";
+    let mut shell = Shell::new();
+
+    let first = shell.eval_program_cached(source, &cache).unwrap();
+    assert!(cache.entry_path(source).exists());
+    assert!(cache.load(source).is_some());
+
+    let second = shell.eval_program_cached(source, &cache).unwrap();
+    assert_eq!(second.exit_code, first.exit_code);
+}
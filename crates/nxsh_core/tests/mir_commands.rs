@@ -0,0 +1,240 @@
+use nxsh_core::mir::commands::{CommandHost, CommandOutput, PipeInput};
+use nxsh_core::mir::{lower::Lowerer, MirError, MirExecutor, MirProgram, MirValue};
+use nxsh_parser::ast::{
+    AssignmentOperator, AstNode, PipeOperator, QuoteType, Redirection, RedirectionOperator,
+    RedirectionTarget, RedirectionType,
+};
+
+fn lower(nodes: Vec<AstNode>) -> MirProgram {
+    Lowerer::new().lower_program(&AstNode::Program(nodes))
+}
+
+fn cmd<'a>(name: &'a str, args: &[&'a str]) -> AstNode<'a> {
+    redirected(name, args, vec![])
+}
+
+fn redirected<'a>(
+    name: &'a str,
+    args: &[&'a str],
+    redirections: Vec<Redirection<'a>>,
+) -> AstNode<'a> {
+    AstNode::Command {
+        name: Box::new(AstNode::Word(name)),
+        args: args.iter().copied().map(AstNode::Word).collect(),
+        redirections,
+        background: false,
+    }
+}
+
+fn redirect(operator: RedirectionOperator, target: &str) -> Redirection<'_> {
+    let redir_type = match operator {
+        RedirectionOperator::Input => RedirectionType::Input,
+        RedirectionOperator::OutputAppend => RedirectionType::Append,
+        _ => RedirectionType::Output,
+    };
+    Redirection {
+        fd: None,
+        operator,
+        target: RedirectionTarget::File(Box::new(AstNode::StringLiteral {
+            value: target,
+            quote_type: QuoteType::Double,
+        })),
+        redir_type,
+    }
+}
+
+fn pipeline(elements: Vec<AstNode<'_>>) -> AstNode<'_> {
+    AstNode::Pipeline {
+        operators: vec![PipeOperator::Pipe; elements.len() - 1],
+        elements,
+    }
+}
+
+fn substitution(command: AstNode<'_>) -> AstNode<'_> {
+    AstNode::CommandSubstitution {
+        command: Box::new(command),
+        is_legacy: false,
+    }
+}
+
+fn assign<'a>(name: &'a str, value: AstNode<'a>) -> AstNode<'a> {
+    AstNode::VariableAssignment {
+        name,
+        operator: AssignmentOperator::Assign,
+        value: Box::new(value),
+        is_local: false,
+        is_export: false,
+        is_readonly: false,
+    }
+}
+
+fn ret(name: &str) -> AstNode<'_> {
+    AstNode::Return(Some(Box::new(AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    })))
+}
+
+/// Answers every command with `<name> <args>` and records what it was given
+#[derive(Default)]
+struct Recorder {
+    calls: Vec<(String, Vec<String>, Option<String>, bool)>,
+}
+
+impl CommandHost for Recorder {
+    fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        capture: bool,
+    ) -> Result<CommandOutput, MirError> {
+        let input = stdin.map(|input| String::from_utf8(input.take_rest()).unwrap());
+        self.calls
+            .push((name.to_string(), args.to_vec(), input, capture));
+        Ok(CommandOutput {
+            status: if name == "false" { 1 } else { 0 },
+            stdout: format!("{name} {}\n", args.join(" ")),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn builtin_output_is_collected() {
+    let program = lower(vec![cmd("echo", &["hello", "world"]), ret("?")]);
+    let mut exec = MirExecutor::new();
+    assert_eq!(exec.execute_main(&program).unwrap(), MirValue::Integer(0));
+    assert_eq!(exec.take_output(), "hello world\n");
+    assert_eq!(exec.take_output(), "");
+}
+
+#[test]
+fn command_substitution_captures_output_without_trailing_newlines() {
+    let program = lower(vec![
+        assign("greeting", substitution(cmd("echo", &["hi"]))),
+        ret("greeting"),
+    ]);
+    let mut exec = MirExecutor::new();
+    assert_eq!(exec.execute_main(&program).unwrap(), MirValue::string("hi"));
+    assert_eq!(exec.take_output(), "");
+}
+
+#[test]
+fn pipeline_stages_read_the_previous_output() {
+    let program = lower(vec![
+        pipeline(vec![cmd("first", &["a"]), cmd("second", &["b"])]),
+        ret("?"),
+    ]);
+    let mut host = Recorder::default();
+    let mut exec = MirExecutor::new();
+    let result = exec.execute_main_with(&program, &mut host).unwrap();
+    assert_eq!(result, MirValue::Integer(0));
+    assert_eq!(
+        host.calls,
+        vec![
+            ("first".into(), vec!["a".into()], None, true),
+            (
+                "second".into(),
+                vec!["b".into()],
+                Some("first a\n".into()),
+                false
+            ),
+        ]
+    );
+    assert_eq!(exec.take_output(), "second b\n");
+}
+
+#[test]
+fn exit_status_of_the_last_command_is_kept() {
+    let program = lower(vec![cmd("false", &[]), ret("?")]);
+    let mut host = Recorder::default();
+    let result = MirExecutor::new()
+        .execute_main_with(&program, &mut host)
+        .unwrap();
+    assert_eq!(result, MirValue::Integer(1));
+}
+
+#[test]
+fn job_group_runs_every_member_and_keeps_the_rightmost_failure() {
+    let program = lower(vec![
+        AstNode::JobGroup(vec![
+            cmd("false", &[]),
+            cmd("first", &["a"]),
+            cmd("false", &["b"]),
+            cmd("second", &[]),
+        ]),
+        ret("?"),
+    ]);
+    let mut host = Recorder::default();
+    let mut exec = MirExecutor::new();
+    let result = exec.execute_main_with(&program, &mut host).unwrap();
+    assert_eq!(result, MirValue::Integer(1));
+    assert_eq!(host.calls.len(), 4);
+    assert_eq!(exec.take_output(), "false \nfirst a\nfalse b\nsecond \n");
+}
+
+#[test]
+fn input_redirection_feeds_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("in.txt");
+    std::fs::write(&path, "line\n").unwrap();
+    let program = lower(vec![redirected(
+        "reader",
+        &[],
+        vec![redirect(RedirectionOperator::Input, path.to_str().unwrap())],
+    )]);
+    let mut host = Recorder::default();
+    MirExecutor::new()
+        .execute_main_with(&program, &mut host)
+        .unwrap();
+    assert_eq!(host.calls[0].2.as_deref(), Some("line\n"));
+}
+
+#[test]
+fn appending_redirection_keeps_earlier_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.txt");
+    let target = path.to_str().unwrap();
+    let program = lower(vec![
+        redirected(
+            "echo",
+            &["one"],
+            vec![redirect(RedirectionOperator::Output, target)],
+        ),
+        redirected(
+            "echo",
+            &["two"],
+            vec![redirect(RedirectionOperator::OutputAppend, target)],
+        ),
+    ]);
+    let mut exec = MirExecutor::new();
+    exec.execute_main(&program).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+    assert_eq!(exec.take_output(), "");
+}
+
+#[test]
+fn missing_command_exits_127() {
+    let program = lower(vec![cmd("nxsh-test-no-such-command", &[]), ret("?")]);
+    let mut exec = MirExecutor::new();
+    assert_eq!(exec.execute_main(&program).unwrap(), MirValue::Integer(127));
+    assert!(exec.take_error_output().contains("command not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn external_commands_read_piped_input() {
+    let program = lower(vec![
+        assign(
+            "upper",
+            substitution(pipeline(vec![
+                cmd("echo", &["hello"]),
+                cmd("tr", &["a-z", "A-Z"]),
+            ])),
+        ),
+        ret("upper"),
+    ]);
+    let result = MirExecutor::new().execute_main(&program).unwrap();
+    assert_eq!(result, MirValue::string("HELLO"));
+}
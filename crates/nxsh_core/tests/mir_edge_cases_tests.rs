@@ -12,8 +12,7 @@ fn test_error_handling_user_functions() {
         .contains("negative numbers not supported"));
 
     // Test factorial with non-integer
-    let result =
-        executor.call_user_function_by_name("factorial", vec![MirValue::String("abc".to_string())]);
+    let result = executor.call_user_function_by_name("factorial", vec![MirValue::string("abc")]);
     assert!(result.is_err());
 
     // Test max with empty arguments
@@ -21,8 +20,7 @@ fn test_error_handling_user_functions() {
     assert!(result.is_err());
 
     // Test map with wrong argument count
-    let result =
-        executor.call_user_function_by_name("map", vec![MirValue::String("double".to_string())]);
+    let result = executor.call_user_function_by_name("map", vec![MirValue::string("double")]);
     assert!(result.is_err());
 
     // Test unknown function
@@ -63,8 +61,8 @@ fn test_boundary_values() {
         .call_user_function_by_name(
             "reduce",
             vec![
-                MirValue::String("add".to_string()),
-                MirValue::Array(vec![]),
+                MirValue::string("add"),
+                MirValue::array(vec![]),
                 MirValue::Integer(42),
             ],
         )
@@ -96,27 +94,27 @@ fn test_type_compatibility() {
         .call_user_function_by_name(
             "reduce",
             vec![
-                MirValue::String("add".to_string()),
-                MirValue::Array(vec![
-                    MirValue::String("Hello".to_string()),
-                    MirValue::String(" ".to_string()),
-                    MirValue::String("World".to_string()),
+                MirValue::string("add"),
+                MirValue::array(vec![
+                    MirValue::string("Hello"),
+                    MirValue::string(" "),
+                    MirValue::string("World"),
                 ]),
             ],
         )
         .unwrap();
-    assert_eq!(result, MirValue::String("Hello World".to_string()));
+    assert_eq!(result, MirValue::string("Hello World"));
 
     // Test filter with different predicates
     let result = executor
         .call_user_function_by_name(
             "filter",
             vec![
-                MirValue::String("notNull".to_string()),
-                MirValue::Array(vec![
+                MirValue::string("notNull"),
+                MirValue::array(vec![
                     MirValue::Integer(1),
                     MirValue::Null,
-                    MirValue::String("test".to_string()),
+                    MirValue::string("test"),
                     MirValue::Null,
                     MirValue::Integer(2),
                 ]),
@@ -127,7 +125,7 @@ fn test_type_compatibility() {
     if let MirValue::Array(arr) = result {
         assert_eq!(arr.len(), 3);
         assert_eq!(arr[0], MirValue::Integer(1));
-        assert_eq!(arr[1], MirValue::String("test".to_string()));
+        assert_eq!(arr[1], MirValue::string("test"));
         assert_eq!(arr[2], MirValue::Integer(2));
     } else {
         // Use safe assertion instead of panic
@@ -0,0 +1,348 @@
+use nxsh_core::mir::{lower::Lowerer, MirExecutor, MirValue};
+use nxsh_parser::ast::{
+    AssignmentOperator, AstNode, BinaryOperator, CaseArm, CatchClause, NumberType, Parameter,
+    ParameterModifier, Pattern, QuoteType, Redirection, RedirectionOperator, RedirectionTarget,
+    RedirectionType,
+};
+
+fn run(nodes: Vec<AstNode>) -> MirValue {
+    let ast = AstNode::Program(nodes);
+    let prog = Lowerer::new().lower_program(&ast);
+    let mut exec = MirExecutor::new();
+    exec.execute_main(&prog).expect("exec main")
+}
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+fn text(value: &str) -> AstNode<'_> {
+    AstNode::StringLiteral {
+        value,
+        quote_type: QuoteType::Double,
+    }
+}
+
+fn var(name: &str) -> AstNode<'_> {
+    AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    }
+}
+
+fn assign<'a>(name: &'a str, value: AstNode<'a>) -> AstNode<'a> {
+    AstNode::VariableAssignment {
+        name,
+        operator: AssignmentOperator::Assign,
+        value: Box::new(value),
+        is_local: false,
+        is_export: false,
+        is_readonly: false,
+    }
+}
+
+fn bin<'a>(left: AstNode<'a>, operator: BinaryOperator, right: AstNode<'a>) -> AstNode<'a> {
+    AstNode::BinaryExpression {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+fn increment(name: &str) -> AstNode<'_> {
+    assign(name, bin(var(name), BinaryOperator::Add, num("1")))
+}
+
+fn cmd<'a>(name: &'a str, args: Vec<AstNode<'a>>) -> AstNode<'a> {
+    AstNode::Command {
+        name: Box::new(AstNode::Word(name)),
+        args,
+        redirections: vec![],
+        background: false,
+    }
+}
+
+fn ret(value: AstNode<'_>) -> AstNode<'_> {
+    AstNode::Return(Some(Box::new(value)))
+}
+
+fn if_then<'a>(condition: AstNode<'a>, body: AstNode<'a>) -> AstNode<'a> {
+    AstNode::If {
+        condition: Box::new(condition),
+        then_branch: Box::new(body),
+        elif_branches: vec![],
+        else_branch: None,
+    }
+}
+
+#[test]
+fn if_elif_else_takes_first_true_arm() {
+    let branch = |n| bin(var("x"), BinaryOperator::Equal, num(n));
+    let result = run(vec![
+        assign("x", num("2")),
+        AstNode::If {
+            condition: Box::new(branch("1")),
+            then_branch: Box::new(text("one")),
+            elif_branches: vec![(branch("2"), text("two")), (branch("2"), text("again"))],
+            else_branch: Some(Box::new(text("other"))),
+        },
+    ]);
+    assert_eq!(result, MirValue::string("two"));
+}
+
+#[test]
+fn while_loop_honours_break_and_continue() {
+    // i=0; s=0; while i < 10 { i++; if i == 3 continue; if i == 6 break; s += i }
+    let body = AstNode::StatementList(vec![
+        increment("i"),
+        if_then(
+            bin(var("i"), BinaryOperator::Equal, num("3")),
+            AstNode::Continue(None),
+        ),
+        if_then(
+            bin(var("i"), BinaryOperator::Equal, num("6")),
+            AstNode::Break(None),
+        ),
+        assign("s", bin(var("s"), BinaryOperator::Add, var("i"))),
+    ]);
+    let result = run(vec![
+        assign("i", num("0")),
+        assign("s", num("0")),
+        AstNode::While {
+            condition: Box::new(bin(var("i"), BinaryOperator::Less, num("10"))),
+            body: Box::new(body),
+        },
+        ret(var("s")),
+    ]);
+    assert_eq!(result, MirValue::Integer(1 + 2 + 4 + 5));
+}
+
+#[test]
+fn until_loop_runs_while_condition_fails() {
+    let result = run(vec![
+        assign("n", num("0")),
+        AstNode::Until {
+            condition: Box::new(bin(var("n"), BinaryOperator::Equal, num("3"))),
+            body: Box::new(increment("n")),
+        },
+        ret(var("n")),
+    ]);
+    assert_eq!(result, MirValue::Integer(3));
+}
+
+#[test]
+fn for_and_c_style_loops_accumulate() {
+    let result = run(vec![
+        assign("total", num("0")),
+        AstNode::For {
+            variable: "n",
+            iterable: Box::new(AstNode::Array(vec![num("1"), num("2"), num("3")])),
+            body: Box::new(assign(
+                "total",
+                bin(var("total"), BinaryOperator::Add, var("n")),
+            )),
+            is_async: false,
+        },
+        AstNode::ForC {
+            init: Some(Box::new(assign("i", num("0")))),
+            condition: Some(Box::new(bin(var("i"), BinaryOperator::Less, num("4")))),
+            update: Some(Box::new(increment("i"))),
+            body: Box::new(assign(
+                "total",
+                bin(var("total"), BinaryOperator::Add, var("i")),
+            )),
+        },
+        ret(var("total")),
+    ]);
+    assert_eq!(result, MirValue::Integer(6 + 6));
+}
+
+#[test]
+fn case_matches_glob_patterns_in_order() {
+    let case = |subject| AstNode::Case {
+        expr: Box::new(text(subject)),
+        arms: vec![
+            CaseArm {
+                patterns: vec![Pattern::Literal("*.md"), Pattern::Literal("*.rst")],
+                body: text("docs"),
+            },
+            CaseArm {
+                patterns: vec![Pattern::Literal("*.txt")],
+                body: text("text"),
+            },
+            CaseArm {
+                patterns: vec![Pattern::Wildcard],
+                body: text("other"),
+            },
+        ],
+    };
+    assert_eq!(run(vec![case("notes.rst")]), MirValue::string("docs"));
+    assert_eq!(run(vec![case("report.txt")]), MirValue::string("text"));
+    assert_eq!(run(vec![case("image.png")]), MirValue::string("other"));
+}
+
+#[test]
+fn functions_receive_named_and_positional_arguments() {
+    let double = AstNode::Function {
+        name: "double",
+        params: vec![Parameter {
+            name: "x",
+            default: None,
+            is_variadic: false,
+        }],
+        body: Box::new(ret(bin(var("x"), BinaryOperator::Multiply, num("2")))),
+        is_async: false,
+        generics: vec![],
+    };
+    let result = run(vec![
+        double,
+        AstNode::FunctionCall {
+            name: Box::new(AstNode::Word("double")),
+            args: vec![num("21")],
+            is_async: false,
+            generics: vec![],
+        },
+    ]);
+    assert_eq!(result, MirValue::Integer(42));
+
+    let result = run(vec![
+        AstNode::Function {
+            name: "second",
+            params: vec![],
+            body: Box::new(ret(var("2"))),
+            is_async: false,
+            generics: vec![],
+        },
+        assign(
+            "picked",
+            cmd("second", vec![AstNode::Word("a"), AstNode::Word("b")]),
+        ),
+        // Positional parameters are restored once the call returns
+        ret(AstNode::Array(vec![var("picked"), var("#")])),
+    ]);
+    assert_eq!(
+        result,
+        MirValue::array(vec![MirValue::string("b"), MirValue::Null])
+    );
+}
+
+#[test]
+fn exit_ends_the_program_with_its_status() {
+    let result = run(vec![
+        AstNode::Exit(Some(Box::new(num("3")))),
+        text("unreachable"),
+    ]);
+    assert_eq!(result, MirValue::Integer(3));
+}
+
+#[test]
+fn throw_is_caught_and_finally_runs() {
+    let result = run(vec![
+        AstNode::Try {
+            body: Box::new(AstNode::StatementList(vec![
+                AstNode::ThrowStatement(Box::new(text("boom"))),
+                assign("skipped", num("1")),
+            ])),
+            catch_clauses: vec![CatchClause {
+                variable: Some("e"),
+                body: Box::new(var("e")),
+            }],
+            finally_clause: Some(Box::new(assign("cleaned", num("1")))),
+        },
+        assign("caught", var("e.message")),
+        ret(AstNode::Array(vec![
+            var("caught"),
+            var("skipped"),
+            var("cleaned"),
+        ])),
+    ]);
+    assert_eq!(
+        result,
+        MirValue::array(vec![
+            MirValue::string("boom"),
+            MirValue::Null,
+            MirValue::Integer(1),
+        ])
+    );
+}
+
+#[test]
+fn uncaught_throw_is_an_error() {
+    let ast = AstNode::Program(vec![AstNode::ThrowStatement(Box::new(text("boom")))]);
+    let prog = Lowerer::new().lower_program(&ast);
+    let err = MirExecutor::new().execute_main(&prog).unwrap_err();
+    assert!(err.to_string().contains("boom"));
+}
+
+#[test]
+fn output_redirection_writes_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    let target = path.to_str().unwrap();
+    let result = run(vec![
+        AstNode::Command {
+            name: Box::new(AstNode::Word("echo")),
+            args: vec![AstNode::Word("hello")],
+            redirections: vec![Redirection {
+                fd: None,
+                operator: RedirectionOperator::Output,
+                target: RedirectionTarget::File(Box::new(AstNode::Word(target))),
+                redir_type: RedirectionType::Output,
+            }],
+            background: false,
+        },
+        ret(var("?")),
+    ]);
+    assert_eq!(result, MirValue::Integer(0));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+}
+
+#[test]
+fn background_job_runs_in_a_subshell() {
+    let result = run(vec![
+        assign("x", num("0")),
+        AstNode::Background(Box::new(assign("x", num("1")))),
+        ret(AstNode::Array(vec![var("x"), var("!")])),
+    ]);
+    assert_eq!(
+        result,
+        MirValue::array(vec![MirValue::Integer(0), MirValue::Integer(1)])
+    );
+}
+
+#[test]
+fn parameter_modifiers_expand() {
+    let modified = |name, modifier| AstNode::VariableExpansion {
+        name,
+        modifier: Some(modifier),
+    };
+    let result = run(vec![
+        assign("path", text("/usr/lib/libfoo.so.1")),
+        ret(AstNode::Array(vec![
+            modified("path", ParameterModifier::RemoveLargestPrefix("*/")),
+            modified("path", ParameterModifier::RemoveSmallestSuffix(".*")),
+            modified("unset", ParameterModifier::UseDefault("fallback")),
+            modified(
+                "path",
+                ParameterModifier::ReplaceAll {
+                    pattern: "/",
+                    replacement: Some(":"),
+                },
+            ),
+            modified("path", ParameterModifier::Length),
+        ])),
+    ]);
+    assert_eq!(
+        result,
+        MirValue::array(vec![
+            MirValue::string("libfoo.so.1"),
+            MirValue::string("/usr/lib/libfoo.so"),
+            MirValue::string("fallback"),
+            MirValue::string(":usr:lib:libfoo.so.1"),
+            MirValue::Integer(20),
+        ])
+    );
+}
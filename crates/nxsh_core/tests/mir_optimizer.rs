@@ -0,0 +1,355 @@
+use nxsh_core::mir::{
+    lower::Lowerer,
+    optimizer::{optimize_function, optimize_program, Cfg},
+    MirBasicBlock, MirExecutor, MirFunction, MirInstruction, MirProgram, MirRegister, MirValue,
+};
+use nxsh_parser::ast::{AssignmentOperator, AstNode, BinaryOperator, NumberType};
+
+fn lower(nodes: Vec<AstNode>) -> MirProgram {
+    Lowerer::new().lower_program(&AstNode::Program(nodes))
+}
+
+fn run(prog: &MirProgram) -> Result<MirValue, String> {
+    MirExecutor::new()
+        .execute_main(prog)
+        .map_err(|e| e.to_string())
+}
+
+/// Runs `nodes` unoptimized and optimized, checks both agree and returns
+/// the optimized program with its result
+fn run_both(nodes: Vec<AstNode>) -> (MirProgram, MirValue) {
+    let plain = lower(nodes);
+    let mut optimized = plain.clone();
+    optimize_program(&mut optimized);
+    let expected = run(&plain).expect("unoptimized run");
+    assert_eq!(run(&optimized).expect("optimized run"), expected);
+    (optimized, expected)
+}
+
+fn main_instructions(prog: &MirProgram) -> Vec<&MirInstruction> {
+    let main = prog.get_function("main").expect("main");
+    let mut ids: Vec<_> = main.blocks.keys().collect();
+    ids.sort();
+    ids.iter()
+        .flat_map(|id| main.blocks[*id].instructions.iter())
+        .collect()
+}
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+fn var(name: &str) -> AstNode<'_> {
+    AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    }
+}
+
+fn assign<'a>(name: &'a str, value: AstNode<'a>) -> AstNode<'a> {
+    AstNode::VariableAssignment {
+        name,
+        operator: AssignmentOperator::Assign,
+        value: Box::new(value),
+        is_local: false,
+        is_export: false,
+        is_readonly: false,
+    }
+}
+
+fn bin<'a>(left: AstNode<'a>, operator: BinaryOperator, right: AstNode<'a>) -> AstNode<'a> {
+    AstNode::BinaryExpression {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+fn ret(value: AstNode<'_>) -> AstNode<'_> {
+    AstNode::Return(Some(Box::new(value)))
+}
+
+fn reg(id: u32) -> MirRegister {
+    MirRegister::new(id)
+}
+
+#[test]
+fn arithmetic_on_constants_is_folded() {
+    let (prog, result) = run_both(vec![ret(bin(
+        num("1"),
+        BinaryOperator::Add,
+        bin(num("2"), BinaryOperator::Multiply, num("3")),
+    ))]);
+    assert_eq!(result, MirValue::Integer(7));
+    assert!(!main_instructions(&prog).iter().any(|inst| matches!(
+        inst,
+        MirInstruction::Add { .. } | MirInstruction::Mul { .. }
+    )));
+}
+
+#[test]
+fn division_by_zero_is_left_for_the_executor() {
+    let mut prog = lower(vec![ret(bin(num("1"), BinaryOperator::Divide, num("0")))]);
+    optimize_program(&mut prog);
+    assert!(main_instructions(&prog)
+        .iter()
+        .any(|inst| matches!(inst, MirInstruction::Div { .. })));
+    assert!(run(&prog).is_err());
+}
+
+#[test]
+fn branch_on_a_constant_drops_the_other_arm() {
+    let nodes = || {
+        vec![
+            AstNode::If {
+                condition: Box::new(bin(num("1"), BinaryOperator::Less, num("2"))),
+                then_branch: Box::new(assign("x", num("10"))),
+                elif_branches: vec![],
+                else_branch: Some(Box::new(assign("x", num("20")))),
+            },
+            ret(var("x")),
+        ]
+    };
+    let blocks_before = lower(nodes()).get_function("main").unwrap().blocks.len();
+    let (prog, result) = run_both(nodes());
+    assert_eq!(result, MirValue::Integer(10));
+    let instructions = main_instructions(&prog);
+    assert!(!instructions
+        .iter()
+        .any(|inst| matches!(inst, MirInstruction::Branch { .. })));
+    assert!(!instructions.iter().any(|inst| matches!(
+        inst,
+        MirInstruction::Store {
+            value: MirValue::Integer(20),
+            ..
+        }
+    )));
+    assert!(prog.get_function("main").unwrap().blocks.len() < blocks_before);
+}
+
+#[test]
+fn constant_left_operand_resolves_short_circuit() {
+    // (0 == 1) && (1 / 0 == 0): the right-hand side is removed with the operator
+    let (prog, result) = run_both(vec![ret(bin(
+        bin(num("0"), BinaryOperator::Equal, num("1")),
+        BinaryOperator::LogicalAnd,
+        bin(
+            bin(num("1"), BinaryOperator::Divide, num("0")),
+            BinaryOperator::Equal,
+            num("0"),
+        ),
+    ))]);
+    assert_eq!(result, MirValue::Boolean(false));
+    assert!(!main_instructions(&prog).iter().any(|inst| matches!(
+        inst,
+        MirInstruction::AndSC { .. } | MirInstruction::Div { .. }
+    )));
+}
+
+#[test]
+fn loops_agree_after_optimization() {
+    // i=0; s=0; while i < 5 { i = i + 1; s = s + i * 2 }
+    let body = AstNode::StatementList(vec![
+        assign("i", bin(var("i"), BinaryOperator::Add, num("1"))),
+        assign(
+            "s",
+            bin(
+                var("s"),
+                BinaryOperator::Add,
+                bin(var("i"), BinaryOperator::Multiply, num("2")),
+            ),
+        ),
+    ]);
+    let (_, result) = run_both(vec![
+        assign("i", num("0")),
+        assign("s", num("0")),
+        AstNode::While {
+            condition: Box::new(bin(var("i"), BinaryOperator::Less, num("5"))),
+            body: Box::new(body),
+        },
+        ret(var("s")),
+    ]);
+    assert_eq!(result, MirValue::Integer(30));
+}
+
+/// Single-block `main` function made of `instructions`
+fn function(instructions: Vec<MirInstruction>) -> MirFunction {
+    let mut func = MirFunction::new("main".to_string(), vec![]);
+    let mut block = MirBasicBlock::new(0);
+    for inst in instructions {
+        block.add_instruction(inst);
+    }
+    func.add_basic_block(block);
+    func
+}
+
+fn program(func: MirFunction) -> MirProgram {
+    let mut prog = MirProgram::new();
+    prog.add_function(func);
+    prog
+}
+
+#[test]
+fn copies_are_propagated_and_removed() {
+    let mut func = function(vec![
+        MirInstruction::Store {
+            dest: "name".into(),
+            value: MirValue::string("nx"),
+        },
+        MirInstruction::Load {
+            dest: reg(0),
+            source: "name".into(),
+        },
+        MirInstruction::Move {
+            dest: reg(1),
+            src: reg(0),
+        },
+        MirInstruction::Move {
+            dest: reg(2),
+            src: reg(1),
+        },
+        MirInstruction::Concat {
+            dest: reg(3),
+            parts: vec![MirValue::Register(reg(2)), MirValue::string("sh")],
+        },
+        MirInstruction::Return {
+            value: Some(MirValue::Register(reg(3))),
+        },
+    ]);
+    let before = run(&program(func.clone())).unwrap();
+    let stats = optimize_function(&mut func, 2);
+    assert!(stats.copies_propagated > 0);
+    let block = func.get_block(0).unwrap();
+    assert!(!block
+        .instructions
+        .iter()
+        .any(|inst| matches!(inst, MirInstruction::Move { .. })));
+    assert!(block.instructions.contains(&MirInstruction::Concat {
+        dest: reg(3),
+        parts: vec![MirValue::Register(reg(0)), MirValue::string("sh")],
+    }));
+    assert_eq!(run(&program(func)).unwrap(), before);
+    assert_eq!(before, MirValue::string("nxsh"));
+}
+
+#[test]
+fn removing_instructions_recounts_short_circuit_skips() {
+    // flag || (2 * 3 == 6), with flag opaque to the optimizer
+    let mut func = function(vec![
+        MirInstruction::Store {
+            dest: "flag".into(),
+            value: MirValue::Boolean(false),
+        },
+        MirInstruction::Load {
+            dest: reg(0),
+            source: "flag".into(),
+        },
+        MirInstruction::OrSC {
+            dest: reg(1),
+            left: MirValue::Register(reg(0)),
+            right: MirValue::Register(reg(4)),
+            skip: 4,
+        },
+        MirInstruction::LoadImmediate {
+            dest: reg(2),
+            value: MirValue::Integer(2),
+        },
+        MirInstruction::Mul {
+            dest: reg(3),
+            left: MirValue::Register(reg(2)),
+            right: MirValue::Integer(3),
+        },
+        MirInstruction::Equal {
+            dest: reg(4),
+            left: MirValue::Register(reg(3)),
+            right: MirValue::Integer(6),
+        },
+        MirInstruction::Move {
+            dest: reg(1),
+            src: reg(4),
+        },
+        MirInstruction::Return {
+            value: Some(MirValue::Register(reg(1))),
+        },
+    ]);
+    optimize_function(&mut func, 2);
+    let instructions = &func.get_block(0).unwrap().instructions;
+    assert_eq!(instructions.len(), 5);
+    assert!(matches!(
+        instructions[2],
+        MirInstruction::OrSC { skip: 1, .. }
+    ));
+    // The result is only known inside the skipped range, so the return keeps
+    // reading the register
+    assert_eq!(
+        instructions[4],
+        MirInstruction::Return {
+            value: Some(MirValue::Register(reg(1))),
+        }
+    );
+    assert_eq!(run(&program(func)).unwrap(), MirValue::Boolean(true));
+}
+
+#[test]
+fn jumps_through_empty_blocks_are_threaded() {
+    // 0 -> 1 -> 2 -> 3: blocks 1 and 2 only jump on
+    let mut func = function(vec![MirInstruction::Jump { target: 1 }]);
+    for (id, inst) in [
+        (1, MirInstruction::Jump { target: 2 }),
+        (2, MirInstruction::Jump { target: 3 }),
+        (
+            3,
+            MirInstruction::Return {
+                value: Some(MirValue::Integer(1)),
+            },
+        ),
+    ] {
+        let mut block = MirBasicBlock::new(id);
+        block.add_instruction(inst);
+        func.add_basic_block(block);
+    }
+    let stats = optimize_function(&mut func, 2);
+    assert!(stats.jumps_threaded > 0);
+    assert_eq!(func.blocks.len(), 1);
+    assert_eq!(
+        func.get_block(0).unwrap().instructions,
+        vec![MirInstruction::Return {
+            value: Some(MirValue::Integer(1)),
+        }]
+    );
+    assert_eq!(run(&program(func)).unwrap(), MirValue::Integer(1));
+}
+
+#[test]
+fn level_zero_leaves_the_program_alone() {
+    let nodes = || vec![ret(bin(num("1"), BinaryOperator::Add, num("2")))];
+    let plain = lower(nodes());
+    let mut prog = lower(nodes());
+    prog.set_optimization_level(0);
+    let stats = optimize_program(&mut prog);
+    assert_eq!(stats.changes(), 0);
+    assert_eq!(main_instructions(&prog), main_instructions(&plain));
+}
+
+#[test]
+fn cfg_tracks_loop_edges() {
+    let prog = lower(vec![
+        assign("i", num("0")),
+        AstNode::While {
+            condition: Box::new(bin(var("i"), BinaryOperator::Less, num("3"))),
+            body: Box::new(assign("i", bin(var("i"), BinaryOperator::Add, num("1")))),
+        },
+    ]);
+    let main = prog.get_function("main").unwrap();
+    let cfg = Cfg::build(main);
+    assert_eq!(cfg.order.first(), Some(&main.entry_block));
+    // The loop header is entered from before the loop and from its body
+    assert!(cfg
+        .order
+        .iter()
+        .any(|id| cfg.predecessors.get(id).is_some_and(|p| p.len() >= 2)));
+    assert!(cfg.order.iter().all(|&id| cfg.is_reachable(id)));
+}
@@ -0,0 +1,206 @@
+#![cfg(unix)]
+mod common;
+use nxsh_core::mir::cache::MirCache;
+use nxsh_core::mir::commands::{
+    CommandHost, CommandOutput, LocalCommands, PipeInput, RunningCommand,
+};
+use nxsh_core::mir::{
+    lower::Lowerer, MirError, MirExecutor, MirFunction, MirInstruction, MirProgram, MirRegister,
+    MirValue,
+};
+use nxsh_parser::ast::{AssignmentOperator, AstNode, PipeOperator};
+use std::fs::File;
+use std::io::Read;
+
+fn lower(nodes: Vec<AstNode>) -> MirProgram {
+    Lowerer::new().lower_program(&AstNode::Program(nodes))
+}
+
+fn cmd<'a>(name: &'a str, args: &[&'a str]) -> AstNode<'a> {
+    AstNode::Command {
+        name: Box::new(AstNode::Word(name)),
+        args: args.iter().copied().map(AstNode::Word).collect(),
+        redirections: vec![],
+        background: false,
+    }
+}
+
+fn pipeline(elements: Vec<AstNode<'_>>) -> AstNode<'_> {
+    AstNode::Pipeline {
+        operators: vec![PipeOperator::Pipe; elements.len() - 1],
+        elements,
+    }
+}
+
+fn ret(name: &str) -> AstNode<'_> {
+    AstNode::Return(Some(Box::new(AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    })))
+}
+
+fn statuses(values: &[i64]) -> MirValue {
+    MirValue::array(values.iter().copied().map(MirValue::Integer).collect())
+}
+
+/// Runs `first`, which prints the first line of its input, in-process and
+/// everything else with the executor's own commands
+#[derive(Default)]
+struct Mixed {
+    local: LocalCommands,
+    pipefail: bool,
+}
+
+impl CommandHost for Mixed {
+    fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        capture: bool,
+    ) -> Result<CommandOutput, MirError> {
+        if name != "first" {
+            return self.local.run(name, args, stdin, capture);
+        }
+        let mut line = Vec::new();
+        let mut input = stdin.cloned().unwrap_or_default();
+        let mut byte = [0];
+        while input.read(&mut byte).unwrap() == 1 {
+            line.push(byte[0]);
+            if byte[0] == b'\n' {
+                break;
+            }
+        }
+        Ok(CommandOutput {
+            status: 0,
+            stdout: String::from_utf8(line).unwrap(),
+            stderr: String::new(),
+        })
+    }
+
+    fn spawn(
+        &mut self,
+        name: &str,
+        args: &[String],
+        stdin: Option<&PipeInput>,
+        stdout: Option<File>,
+        capture: bool,
+    ) -> Option<RunningCommand> {
+        match name {
+            "first" => None,
+            _ => self.local.spawn(name, args, stdin, stdout, capture),
+        }
+    }
+
+    fn option(&self, name: &str) -> bool {
+        name == "pipefail" && self.pipefail
+    }
+}
+
+#[test]
+fn external_stages_stream_into_each_other() {
+    // `yes` never ends on its own: the pipeline only finishes if `head` reads
+    // it while it runs and `yes` stops once `head` has gone
+    let program = lower(vec![
+        AstNode::VariableAssignment {
+            name: "lines",
+            operator: AssignmentOperator::Assign,
+            value: Box::new(AstNode::CommandSubstitution {
+                command: Box::new(pipeline(vec![cmd("yes", &[]), cmd("head", &["-n", "3"])])),
+                is_legacy: false,
+            }),
+            is_local: false,
+            is_export: false,
+            is_readonly: false,
+        },
+        ret("lines"),
+    ]);
+    let result = MirExecutor::new().execute_main(&program).unwrap();
+    assert_eq!(result, MirValue::string("y\ny\ny"));
+}
+
+#[test]
+fn in_process_stages_read_a_running_process() {
+    let program = lower(vec![
+        pipeline(vec![cmd("yes", &["line"]), cmd("first", &[])]),
+        ret("PIPESTATUS"),
+    ]);
+    let mut host = Mixed::default();
+    let mut exec = MirExecutor::new();
+    let result = exec.execute_main_with(&program, &mut host).unwrap();
+    // `yes` ends on SIGPIPE once `first` stops reading
+    assert_eq!(result, statuses(&[141, 0]));
+    assert_eq!(exec.take_output(), "line\n");
+}
+
+#[test]
+fn every_stage_status_is_kept_and_pipefail_picks_the_rightmost_failure() {
+    let program = lower(vec![
+        pipeline(vec![
+            cmd("sh", &["-c", "exit 3"]),
+            cmd("sh", &["-c", "exit 4"]),
+            cmd("true", &[]),
+        ]),
+        AstNode::Return(Some(Box::new(AstNode::Array(vec![
+            AstNode::VariableExpansion {
+                name: "?",
+                modifier: None,
+            },
+            AstNode::VariableExpansion {
+                name: "PIPESTATUS",
+                modifier: None,
+            },
+        ])))),
+    ]);
+    for (pipefail, status) in [(false, 0), (true, 4)] {
+        let mut host = Mixed {
+            pipefail,
+            ..Mixed::default()
+        };
+        let result = MirExecutor::new()
+            .execute_main_with(&program, &mut host)
+            .unwrap();
+        assert_eq!(
+            result,
+            MirValue::array(vec![MirValue::Integer(status), statuses(&[3, 4, 0])])
+        );
+    }
+}
+
+#[test]
+fn stack_engine_runs_pipelines() {
+    let (r0, r1) = (MirRegister::new(0), MirRegister::new(1));
+    let stage =
+        |words: &[&str]| MirValue::array(words.iter().map(|w| MirValue::string(*w)).collect());
+    let mut main = MirFunction::new("main".to_string(), vec![]);
+    let entry = main.get_block_mut(0).unwrap();
+    entry.add_instruction(MirInstruction::LoadImmediate {
+        dest: r0.clone(),
+        value: stage(&["sh", "-c", "exit 5"]),
+    });
+    entry.add_instruction(MirInstruction::PipelineStart);
+    entry.add_instruction(MirInstruction::PipelineAdd {
+        command: r0.clone(),
+    });
+    entry.add_instruction(MirInstruction::PipelineAdd { command: r0 });
+    entry.add_instruction(MirInstruction::PipelineExec { dest: r1.clone() });
+    entry.add_instruction(MirInstruction::Return {
+        value: Some(MirValue::Register(r1)),
+    });
+    let mut program = MirProgram::new();
+    program.add_function(main);
+
+    let result = MirExecutor::new().execute(&program).unwrap();
+    assert_eq!(result, MirValue::Integer(5));
+}
+
+#[test]
+fn shell_scripts_stream_pipelines() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let mut shell = common::shell();
+    let res = shell
+        .eval_program_cached("echo $(yes | head -n 3 | wc -l)\n", &cache)
+        .unwrap();
+    assert_eq!(res.stdout, "3\n");
+}
@@ -0,0 +1,157 @@
+mod common;
+use common::shell_in;
+use nxsh_core::mir::profile::Profile;
+use nxsh_core::mir::{lower::Lowerer, MirExecutor};
+use nxsh_parser::ast::{AstNode, BinaryOperator, NumberType, Parameter};
+use std::fs;
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+/// `double(x) = x * 2`, called for 1, 2 and 3
+fn program() -> AstNode<'static> {
+    let double = AstNode::Function {
+        name: "double",
+        params: vec![Parameter {
+            name: "x",
+            default: None,
+            is_variadic: false,
+        }],
+        body: Box::new(AstNode::Return(Some(Box::new(AstNode::BinaryExpression {
+            left: Box::new(AstNode::VariableExpansion {
+                name: "x",
+                modifier: None,
+            }),
+            operator: BinaryOperator::Multiply,
+            right: Box::new(num("2")),
+        })))),
+        is_async: false,
+        generics: vec![],
+    };
+    let call = AstNode::FunctionCall {
+        name: Box::new(AstNode::Word("double")),
+        args: vec![AstNode::VariableExpansion {
+            name: "n",
+            modifier: None,
+        }],
+        is_async: false,
+        generics: vec![],
+    };
+    AstNode::Program(vec![
+        double,
+        AstNode::For {
+            variable: "n",
+            iterable: Box::new(AstNode::Array(vec![num("1"), num("2"), num("3")])),
+            body: Box::new(call),
+            is_async: false,
+        },
+    ])
+}
+
+fn profiled() -> (MirExecutor, Profile) {
+    let program = Lowerer::new().lower_program(&program());
+    let mut exec = MirExecutor::new();
+    exec.start_profiling();
+    exec.execute_main(&program).unwrap();
+    let profile = exec.take_profile().expect("profile recorded");
+    (exec, profile)
+}
+
+#[test]
+fn calls_are_counted_per_function() {
+    let (_, profile) = profiled();
+    assert_eq!(profile.functions["main"].calls, 1);
+    assert_eq!(profile.functions["double"].calls, 3);
+    // main's time includes the calls it made
+    assert!(profile.functions["main"].total_ns >= profile.functions["double"].total_ns);
+    assert_eq!(profile.top_functions(1)[0].0, "main");
+}
+
+#[test]
+fn instructions_add_up_by_opcode_function_and_block() {
+    let (exec, profile) = profiled();
+    let executed = profile.instructions_executed;
+    assert_eq!(executed, exec.stats().instructions_executed);
+    assert_eq!(profile.opcodes.values().sum::<u64>(), executed);
+    assert_eq!(
+        profile
+            .functions
+            .values()
+            .map(|f| f.instructions)
+            .sum::<u64>(),
+        executed
+    );
+    let double = &profile.functions["double"];
+    assert_eq!(
+        double.blocks.values().map(|b| b.instructions).sum::<u64>(),
+        double.instructions
+    );
+    assert!(double.blocks.values().any(|b| b.entries == 3));
+    assert!(profile.opcodes["Return"] >= 3);
+}
+
+#[test]
+fn hot_blocks_and_opcodes_are_ordered() {
+    let (_, profile) = profiled();
+    let hot = profile.hot_blocks(usize::MAX);
+    assert!(hot
+        .windows(2)
+        .all(|w| w[0].profile.instructions >= w[1].profile.instructions));
+    assert_eq!(profile.hot_blocks(2).len(), 2);
+    let opcodes = profile.top_opcodes(3);
+    assert_eq!(opcodes.len(), 3);
+    assert!(opcodes.windows(2).all(|w| w[0].1 >= w[1].1));
+}
+
+#[test]
+fn profile_round_trips_through_json() {
+    let (_, profile) = profiled();
+    let loaded: Profile = serde_json::from_str(&profile.to_json()).unwrap();
+    assert_eq!(loaded.functions, profile.functions);
+    assert_eq!(loaded.opcodes, profile.opcodes);
+}
+
+#[test]
+fn profiling_is_off_until_started() {
+    let program = Lowerer::new().lower_program(&program());
+    let mut exec = MirExecutor::new();
+    exec.execute_main(&program).unwrap();
+    assert!(exec.take_profile().is_none());
+}
+
+#[test]
+fn profile_builtin_runs_scripts_and_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("script.sh"), "echo one\necho two\n").unwrap();
+    let mut sh = shell_in(&dir);
+
+    let res = sh.eval_program("profile report").unwrap();
+    assert_eq!(res.exit_code, 1);
+
+    let res = sh
+        .eval_program("profile run -o profile.json ./script.sh")
+        .unwrap();
+    assert_eq!(res.stdout, "one\ntwo\n");
+    let exported: Profile =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("profile.json")).unwrap())
+            .unwrap();
+    assert_eq!(exported.functions["main"].calls, 1);
+    assert!(exported.opcodes["ExecuteCommand"] >= 2);
+
+    let res = sh.eval_program("profile report --top 5").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(res.stdout.contains("Hot blocks"));
+    assert!(res.stdout.contains("ExecuteCommand"));
+
+    let res = sh.eval_program("profile report --json").unwrap();
+    let reported: Profile = serde_json::from_str(&res.stdout).unwrap();
+    assert_eq!(reported.functions, exported.functions);
+
+    let res = sh.eval_program("profile frobnicate").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res.stderr.contains("usage"));
+}
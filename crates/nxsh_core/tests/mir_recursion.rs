@@ -0,0 +1,186 @@
+mod common;
+use nxsh_core::mir::cache::MirCache;
+use nxsh_core::mir::{
+    lower::Lowerer, MirError, MirExecutor, MirFunction, MirInstruction, MirProgram, MirRegister,
+    MirValue, DEFAULT_MAX_CALL_DEPTH,
+};
+use nxsh_parser::ast::{AstNode, BinaryOperator, NumberType, Parameter};
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+fn n() -> AstNode<'static> {
+    AstNode::VariableExpansion {
+        name: "n",
+        modifier: None,
+    }
+}
+
+fn binary<'a>(left: AstNode<'a>, operator: BinaryOperator, right: AstNode<'a>) -> AstNode<'a> {
+    AstNode::BinaryExpression {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+fn call<'a>(name: &'a str, arg: AstNode<'a>) -> AstNode<'a> {
+    AstNode::FunctionCall {
+        name: Box::new(AstNode::Word(name)),
+        args: vec![arg],
+        is_async: false,
+        generics: vec![],
+    }
+}
+
+fn ret(value: AstNode<'_>) -> AstNode<'_> {
+    AstNode::Return(Some(Box::new(value)))
+}
+
+/// `name(n)`: 0 once `n` is 0, otherwise `result` computed from `n - 1`
+fn recursive<'a>(name: &'a str, result: AstNode<'a>) -> AstNode<'a> {
+    AstNode::Function {
+        name,
+        params: vec![Parameter {
+            name: "n",
+            default: None,
+            is_variadic: false,
+        }],
+        body: Box::new(AstNode::Program(vec![
+            AstNode::If {
+                condition: Box::new(binary(n(), BinaryOperator::Equal, num("0"))),
+                then_branch: Box::new(ret(num("0"))),
+                elif_branches: vec![],
+                else_branch: None,
+            },
+            ret(result),
+        ])),
+        is_async: false,
+        generics: vec![],
+    }
+}
+
+/// `countdown(n)` calls itself last; `depth(n)` adds 1 to what it returns
+fn program(function: &str, arg: &str) -> MirProgram {
+    let minus_one = || binary(n(), BinaryOperator::Subtract, num("1"));
+    let countdown = recursive("countdown", call("countdown", minus_one()));
+    let depth = recursive(
+        "depth",
+        binary(call("depth", minus_one()), BinaryOperator::Add, num("1")),
+    );
+    Lowerer::new().lower_program(&AstNode::Program(vec![
+        countdown,
+        depth,
+        ret(call(function, num(arg))),
+    ]))
+}
+
+fn tail_calls(program: &MirProgram, function: &str) -> usize {
+    program
+        .get_function(function)
+        .unwrap()
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter(|inst| matches!(inst, MirInstruction::TailCall { .. }))
+        .count()
+}
+
+#[test]
+fn lowering_marks_only_calls_whose_result_is_returned() {
+    let program = program("countdown", "1");
+    assert_eq!(tail_calls(&program, "countdown"), 1);
+    assert_eq!(tail_calls(&program, "depth"), 0);
+    assert_eq!(tail_calls(&program, "main"), 0);
+}
+
+#[test]
+fn tail_calls_reuse_the_frame() {
+    let mut exec = MirExecutor::new();
+    exec.set_max_call_depth(10);
+    let result = exec.execute_main(&program("countdown", "100000")).unwrap();
+    assert_eq!(result, MirValue::Integer(0));
+    assert!(exec.stats().function_calls > 100_000);
+}
+
+#[test]
+fn deep_recursion_does_not_grow_the_native_stack() {
+    let mut exec = MirExecutor::new();
+    assert_eq!(exec.max_call_depth(), DEFAULT_MAX_CALL_DEPTH);
+    let result = exec.execute_main(&program("depth", "5000")).unwrap();
+    assert_eq!(result, MirValue::Integer(5000));
+}
+
+#[test]
+fn nesting_beyond_the_limit_fails_cleanly() {
+    let mut exec = MirExecutor::new();
+    exec.set_max_call_depth(50);
+    match exec.execute_main(&program("depth", "100")) {
+        Err(MirError::Runtime(message)) => assert_eq!(
+            message,
+            "depth: maximum function nesting level exceeded (50)"
+        ),
+        other => panic!("expected a nesting error, got {other:?}"),
+    }
+    // 失敗した呼び出しは片付いている
+    let result = exec.execute_main(&program("depth", "49")).unwrap();
+    assert_eq!(result, MirValue::Integer(49));
+}
+
+#[test]
+fn stack_engine_calls_functions_of_the_program() {
+    // main は twice(21) を返し、twice(x) は x + x を返す
+    let (r0, r1) = (MirRegister::new(0), MirRegister::new(1));
+    let mut main = MirFunction::new("main".to_string(), vec![]);
+    let entry = main.get_block_mut(0).unwrap();
+    entry.add_instruction(MirInstruction::Call {
+        dest: r0.clone(),
+        function: "twice".to_string(),
+        args: vec![MirValue::Integer(21)],
+    });
+    entry.add_instruction(MirInstruction::Return {
+        value: Some(MirValue::Register(r0.clone())),
+    });
+    let mut twice = MirFunction::new("twice".to_string(), vec!["x".to_string()]);
+    let body = twice.get_block_mut(0).unwrap();
+    body.add_instruction(MirInstruction::Load {
+        dest: r0.clone(),
+        source: "x".to_string(),
+    });
+    body.add_instruction(MirInstruction::Add {
+        dest: r1.clone(),
+        left: MirValue::Register(r0.clone()),
+        right: MirValue::Register(r0),
+    });
+    body.add_instruction(MirInstruction::Return {
+        value: Some(MirValue::Register(r1)),
+    });
+    let mut program = MirProgram::new();
+    program.add_function(main);
+    program.add_function(twice);
+
+    let mut exec = MirExecutor::new();
+    assert_eq!(exec.execute(&program).unwrap(), MirValue::Integer(42));
+    let result = exec
+        .call_user_function_by_name("twice", vec![MirValue::Integer(5)])
+        .unwrap();
+    assert_eq!(result, MirValue::Integer(10));
+}
+
+#[test]
+fn funcnest_limits_function_nesting_in_the_shell() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let mut shell = common::shell();
+    shell.context_mut().set_var("FUNCNEST", "5");
+    let err = shell
+        .eval_program_cached("nest() { nest; true; }\nnest\n", &cache)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("nest: maximum function nesting level exceeded (5)"));
+}
@@ -0,0 +1,280 @@
+mod common;
+use nxsh_core::mir::cache::MirCache;
+use nxsh_core::mir::{
+    lower::Lowerer, MirError, MirExecutor, MirFunction, MirInstruction, MirProgram, MirRegister,
+    MirValue,
+};
+use nxsh_parser::ast::{
+    AssignmentOperator, AstNode, BinaryOperator, CatchClause, NumberType, QuoteType,
+};
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+fn text(value: &str) -> AstNode<'_> {
+    AstNode::StringLiteral {
+        value,
+        quote_type: QuoteType::Double,
+    }
+}
+
+fn var(name: &str) -> AstNode<'_> {
+    AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    }
+}
+
+fn assign<'a>(name: &'a str, value: AstNode<'a>) -> AstNode<'a> {
+    AstNode::VariableAssignment {
+        name,
+        operator: AssignmentOperator::Assign,
+        value: Box::new(value),
+        is_local: false,
+        is_export: false,
+        is_readonly: false,
+    }
+}
+
+fn throw(message: &str) -> AstNode<'_> {
+    AstNode::ThrowStatement(Box::new(text(message)))
+}
+
+/// `try { body } catch err { catch } finally { finally }`
+fn try_catch<'a>(
+    body: AstNode<'a>,
+    catch: Option<AstNode<'a>>,
+    finally: Option<AstNode<'a>>,
+) -> AstNode<'a> {
+    AstNode::Try {
+        body: Box::new(body),
+        catch_clauses: catch
+            .into_iter()
+            .map(|body| CatchClause {
+                variable: Some("err"),
+                body: Box::new(body),
+            })
+            .collect(),
+        finally_clause: finally.map(Box::new),
+    }
+}
+
+/// Runs the statements and returns `result`
+fn run<'a>(mut nodes: Vec<AstNode<'a>>, result: AstNode<'a>) -> Result<MirValue, MirError> {
+    nodes.push(AstNode::Return(Some(Box::new(result))));
+    let program = Lowerer::new().lower_program(&AstNode::Program(nodes));
+    MirExecutor::new().execute_main(&program)
+}
+
+fn values(items: Vec<MirValue>) -> MirValue {
+    MirValue::array(items)
+}
+
+#[test]
+fn catch_body_sees_message_and_code() {
+    let thrown = AstNode::ThrowStatement(Box::new(AstNode::ArgumentList(vec![
+        text("disk full"),
+        AstNode::Word("28"),
+    ])));
+    let result = run(
+        vec![try_catch(
+            thrown,
+            Some(AstNode::StatementList(vec![
+                assign("message", var("err.message")),
+                assign("code", var("err.code")),
+            ])),
+            None,
+        )],
+        AstNode::Array(vec![var("message"), var("code")]),
+    );
+    assert_eq!(
+        result.unwrap(),
+        values(vec![MirValue::string("disk full"), MirValue::Integer(28)])
+    );
+}
+
+#[test]
+fn runtime_errors_are_caught_as_error_objects() {
+    let result = run(
+        vec![
+            assign("zero", num("0")),
+            try_catch(
+                assign(
+                    "x",
+                    AstNode::BinaryExpression {
+                        left: Box::new(num("1")),
+                        operator: BinaryOperator::Divide,
+                        right: Box::new(var("zero")),
+                    },
+                ),
+                Some(assign("code", var("err.code"))),
+                None,
+            ),
+        ],
+        AstNode::Array(vec![var("err.message"), var("code")]),
+    );
+    assert_eq!(
+        result.unwrap(),
+        values(vec![
+            MirValue::string("division by zero"),
+            MirValue::Integer(1)
+        ])
+    );
+}
+
+#[test]
+fn finally_runs_before_an_error_from_the_catch_body_leaves() {
+    let inner = try_catch(
+        throw("first"),
+        Some(throw("second")),
+        Some(assign("cleaned", num("1"))),
+    );
+    let result = run(
+        vec![try_catch(
+            inner,
+            Some(assign("seen", var("err.message"))),
+            None,
+        )],
+        AstNode::Array(vec![var("seen"), var("cleaned")]),
+    );
+    assert_eq!(
+        result.unwrap(),
+        values(vec![MirValue::string("second"), MirValue::Integer(1)])
+    );
+}
+
+#[test]
+fn rethrowing_the_error_keeps_its_code() {
+    let thrown = AstNode::ThrowStatement(Box::new(AstNode::ArgumentList(vec![
+        text("gone"),
+        AstNode::Word("127"),
+    ])));
+    let inner = try_catch(
+        thrown,
+        Some(AstNode::ThrowStatement(Box::new(var("err")))),
+        None,
+    );
+    let result = run(
+        vec![try_catch(
+            inner,
+            Some(assign("code", var("err.code"))),
+            None,
+        )],
+        AstNode::Array(vec![var("err.message"), var("code")]),
+    );
+    assert_eq!(
+        result.unwrap(),
+        values(vec![MirValue::string("gone"), MirValue::Integer(127)])
+    );
+}
+
+#[test]
+fn errors_unwind_out_of_function_calls() {
+    let fail = AstNode::Function {
+        name: "fail",
+        params: vec![],
+        body: Box::new(AstNode::StatementList(vec![
+            throw("deep"),
+            assign("unreached", num("1")),
+        ])),
+        is_async: false,
+        generics: vec![],
+    };
+    let call = AstNode::FunctionCall {
+        name: Box::new(AstNode::Word("fail")),
+        args: vec![],
+        is_async: false,
+        generics: vec![],
+    };
+    let result = run(
+        vec![
+            fail,
+            try_catch(
+                AstNode::StatementList(vec![call, assign("after", num("1"))]),
+                Some(assign("seen", var("err.message"))),
+                None,
+            ),
+        ],
+        AstNode::Array(vec![var("seen"), var("after"), var("unreached")]),
+    );
+    assert_eq!(
+        result.unwrap(),
+        values(vec![
+            MirValue::string("deep"),
+            MirValue::Null,
+            MirValue::Null
+        ])
+    );
+}
+
+#[test]
+fn uncaught_throw_carries_message_and_code() {
+    let thrown = AstNode::ThrowStatement(Box::new(AstNode::ArgumentList(vec![
+        text("nope"),
+        num("3"),
+    ])));
+    match run(vec![try_catch(thrown, None, None)], num("0")) {
+        Err(MirError::Thrown { message, code }) => {
+            assert_eq!(message, "nope");
+            assert_eq!(code, 3);
+        }
+        other => panic!("expected a thrown error, got {other:?}"),
+    }
+}
+
+#[test]
+fn dotted_names_on_plain_values_keep_the_dot() {
+    let result = run(vec![assign("file", text("notes"))], var("file.txt"));
+    assert_eq!(result.unwrap(), MirValue::string("notes.txt"));
+}
+
+#[test]
+fn stack_engine_enters_try_handlers() {
+    // try { throw "legacy" } の handler で受けたエラーのメッセージを返す
+    let (r0, r1) = (MirRegister::new(0), MirRegister::new(1));
+    let mut main = MirFunction::new("main".to_string(), vec![]);
+    let handler = main.create_block();
+    let entry = main.get_block_mut(0).unwrap();
+    entry.add_instruction(MirInstruction::TryBegin {
+        handler_block: handler,
+    });
+    entry.add_instruction(MirInstruction::Throw {
+        value: MirValue::string("legacy"),
+    });
+    entry.add_instruction(MirInstruction::TryEnd);
+    entry.add_instruction(MirInstruction::Return { value: None });
+    let catch = main.get_block_mut(handler).unwrap();
+    catch.add_instruction(MirInstruction::Caught { dest: r0.clone() });
+    catch.add_instruction(MirInstruction::ObjectGet {
+        dest: r1.clone(),
+        object: MirValue::Register(r0),
+        field: "message".to_string(),
+    });
+    catch.add_instruction(MirInstruction::Return {
+        value: Some(MirValue::Register(r1)),
+    });
+    let mut program = MirProgram::new();
+    program.add_function(main);
+
+    let result = MirExecutor::new().execute(&program).unwrap();
+    assert_eq!(result, MirValue::string("legacy"));
+}
+
+#[test]
+fn shell_scripts_use_try_catch_finally() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MirCache::new(dir.path());
+    let mut shell = common::shell();
+    let script = "try {\n  throw \"disk full\" 28\n  echo skipped\n} catch err {\n  echo $err.message $err.code\n} finally {\n  echo cleanup\n}\n";
+    let res = shell.eval_program_cached(script, &cache).unwrap();
+    assert_eq!(res.stdout, "disk full 28\ncleanup\n");
+
+    let err = shell
+        .eval_program_cached("throw 'not caught'\n", &cache)
+        .unwrap_err();
+    assert!(err.to_string().contains("not caught"));
+}
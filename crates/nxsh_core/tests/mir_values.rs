@@ -0,0 +1,109 @@
+use nxsh_core::mir::{lower::Lowerer, MirExecutor, MirInstruction, MirValue};
+use nxsh_parser::ast::{ArrayElement, AssignmentOperator, AstNode, NumberType, QuoteType};
+use std::sync::Arc;
+
+fn num(value: &str) -> AstNode<'_> {
+    AstNode::NumberLiteral {
+        value,
+        number_type: NumberType::Decimal,
+    }
+}
+
+fn text(value: &str) -> AstNode<'_> {
+    AstNode::StringLiteral {
+        value,
+        quote_type: QuoteType::Double,
+    }
+}
+
+fn var(name: &str) -> AstNode<'_> {
+    AstNode::VariableExpansion {
+        name,
+        modifier: None,
+    }
+}
+
+fn assign<'a>(name: &'a str, value: AstNode<'a>) -> AstNode<'a> {
+    AstNode::VariableAssignment {
+        name,
+        operator: AssignmentOperator::Assign,
+        value: Box::new(value),
+        is_local: false,
+        is_export: false,
+        is_readonly: false,
+    }
+}
+
+fn array<'a>(name: &'a str, operator: AssignmentOperator, values: &[&'a str]) -> AstNode<'a> {
+    AstNode::ArrayAssignment {
+        name,
+        operator,
+        elements: values
+            .iter()
+            .map(|value| ArrayElement {
+                index: None,
+                value: num(value),
+            })
+            .collect(),
+        is_local: false,
+        is_export: false,
+    }
+}
+
+fn ints(values: &[i64]) -> MirValue {
+    MirValue::array(values.iter().copied().map(MirValue::Integer).collect())
+}
+
+#[test]
+fn clones_share_strings_and_arrays() {
+    let string = MirValue::string("shared text");
+    let array = MirValue::array(vec![string.clone()]);
+    match (&string, string.clone()) {
+        (MirValue::String(a), MirValue::String(b)) => assert!(Arc::ptr_eq(a, &b)),
+        other => panic!("expected strings, got {other:?}"),
+    }
+    match (&array, array.clone()) {
+        (MirValue::Array(a), MirValue::Array(b)) => assert!(Arc::ptr_eq(a, &b)),
+        other => panic!("expected arrays, got {other:?}"),
+    }
+}
+
+#[test]
+fn lowering_interns_repeated_literals() {
+    let program = Lowerer::new().lower_program(&AstNode::Program(vec![
+        assign("x", text("same")),
+        assign("y", text("same")),
+    ]));
+    let strings: Vec<Arc<str>> = program
+        .get_function("main")
+        .unwrap()
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|inst| match inst {
+            MirInstruction::LoadImmediate {
+                value: MirValue::String(s),
+                ..
+            } => Some(s.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(strings.len(), 2);
+    assert!(Arc::ptr_eq(&strings[0], &strings[1]));
+}
+
+#[test]
+fn updating_an_array_leaves_its_copies_alone() {
+    // a=(1 2); b=$a; a+=(3)
+    let program = Lowerer::new().lower_program(&AstNode::Program(vec![
+        array("a", AssignmentOperator::Assign, &["1", "2"]),
+        assign("b", var("a")),
+        array("a", AssignmentOperator::AddAssign, &["3"]),
+        AstNode::Return(Some(Box::new(AstNode::Array(vec![var("a"), var("b")])))),
+    ]));
+    let result = MirExecutor::new().execute_main(&program).unwrap();
+    assert_eq!(
+        result,
+        MirValue::array(vec![ints(&[1, 2, 3]), ints(&[1, 2])])
+    );
+}
@@ -0,0 +1,129 @@
+//! `nice` runs programs at another priority; `renice` changes the priority
+//! of ones already running
+#![cfg(unix)]
+mod common;
+use common::{script, shell};
+use nxsh_core::Shell;
+
+/// The shell's own nice value, which the tests adjust from
+fn base(sh: &mut Shell) -> i32 {
+    sh.eval_program("nice")
+        .unwrap()
+        .stdout
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn programs_run_with_the_adjusted_nice_value() {
+    let dir = tempfile::tempdir().unwrap();
+    // The system `nice` prints the nice value it runs at
+    let report = script(&dir, "report", "exec nice\n").display().to_string();
+    let mut sh = shell();
+    let base = base(&mut sh);
+    let niced = |sh: &mut Shell, command: &str| {
+        let res = sh.eval_program(&format!("echo $({command})")).unwrap();
+        res.stdout.trim().parse::<i32>().unwrap()
+    };
+    assert_eq!(
+        niced(&mut sh, &format!("nice {report}")),
+        (base + 10).min(19)
+    );
+    assert_eq!(
+        niced(&mut sh, &format!("nice -n 5 {report}")),
+        (base + 5).min(19)
+    );
+    assert_eq!(
+        niced(&mut sh, &format!("nice -3 {report}")),
+        (base + 3).min(19)
+    );
+    // Nested, the adjustments add up
+    assert_eq!(
+        niced(&mut sh, &format!("nice -n 1 nice -n 2 {report}")),
+        (base + 3).min(19)
+    );
+    assert_eq!(niced(&mut sh, "nice -n 5 nice"), (base + 5).min(19));
+    // Later programs keep the shell's priority
+    assert_eq!(niced(&mut sh, &report), base);
+}
+
+#[test]
+fn functions_and_pipelines_pass_the_priority_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = script(&dir, "report", "exec nice\n").display().to_string();
+    let mut sh = shell();
+    let base = base(&mut sh);
+    sh.eval_program(&format!("function build() {{ {report}; }}"))
+        .unwrap();
+    let res = sh.eval_program("echo $(nice -n 4 build)").unwrap();
+    assert_eq!(res.stdout, format!("{}\n", (base + 4).min(19)));
+    let res = sh
+        .eval_program(&format!("echo $(nice -n 4 {report} | cat)"))
+        .unwrap();
+    assert_eq!(res.stdout, format!("{}\n", (base + 4).min(19)));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cpu_list_pins_programs_to_the_cpus() {
+    let dir = tempfile::tempdir().unwrap();
+    let pinned = script(&dir, "pinned", "grep Cpus_allowed_list /proc/$$/status\n");
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("echo $(nice --cpu-list 0 {})", pinned.display()))
+        .unwrap();
+    assert!(res.stdout.ends_with(":\t0\n"), "{}", res.stdout);
+    let res = sh.eval_program("nice --cpu-list 100000 true").unwrap();
+    assert_eq!(res.exit_code, 125);
+    assert!(res.stderr.contains("CPU 100000"), "{}", res.stderr);
+}
+
+#[test]
+fn usage_errors_exit_125() {
+    let mut sh = shell();
+    for command in [
+        "nice -n",
+        "nice -n soon true",
+        "nice -n 5",
+        "nice --cpu-list all true",
+    ] {
+        let res = sh.eval_program(command).unwrap();
+        assert_eq!(res.exit_code, 125, "{command}");
+        assert!(res.stderr.contains("nice: usage"), "{command}");
+    }
+    let res = sh.eval_program("nice nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    let res = sh.eval_program("nice return").unwrap();
+    assert_eq!(res.exit_code, 126);
+}
+
+#[test]
+fn renice_changes_running_jobs() {
+    let mut sh = shell();
+    let base = base(&mut sh);
+    sh.eval_program("sleep 10 > /dev/null &").unwrap();
+    let target = (base + 3).min(19);
+    let res = sh.eval_program(&format!("renice {target} %1")).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert!(
+        res.stdout
+            .ends_with(&format!("old priority {base}, new priority {target}\n")),
+        "{}",
+        res.stdout
+    );
+    let res = sh.eval_program("renice -n 1 --relative %1").unwrap();
+    let relative = (target + 1).min(19);
+    assert!(
+        res.stdout.ends_with(&format!("new priority {relative}\n")),
+        "{}",
+        res.stdout
+    );
+    sh.eval_program("kill %1").unwrap();
+
+    let res = sh.eval_program("renice 5 %9").unwrap();
+    assert_eq!(res.exit_code, 1);
+    let res = sh.eval_program("renice 5").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("renice: usage"));
+}
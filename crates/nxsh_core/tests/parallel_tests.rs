@@ -0,0 +1,164 @@
+//! `parallel` fans work out as background jobs; `wait -n` picks up whichever
+//! finishes first
+#![cfg(unix)]
+mod common;
+use common::shell;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// A job that sleeps for its first argument, prints it and exits with its
+/// second
+fn job(dir: &tempfile::TempDir) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.path().join("job");
+    std::fs::write(&path, "#!/bin/sh\nsleep $1\necho $1\nexit $2\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn runs_the_command_for_each_argument() {
+    let mut sh = shell();
+    let res = sh.eval_program("parallel -j2 -k echo ::: a b c").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stdout, "a\nb\nc\n");
+}
+
+#[test]
+fn keep_order_prints_in_argument_order_not_finishing_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir).display().to_string();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("parallel -j3 -k {job} {{}} 0 ::: 0.3 0.1 0"))
+        .unwrap();
+    assert_eq!(res.stdout, "0.3\n0.1\n0\n");
+    let res = sh
+        .eval_program(&format!("parallel -j3 {job} {{}} 0 ::: 0.3 0"))
+        .unwrap();
+    assert_eq!(res.stdout, "0\n0.3\n");
+}
+
+#[test]
+fn replacement_strings() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("parallel -k echo {} {.} {/} {//} {/.} ::: dir/a.tar.gz b")
+        .unwrap();
+    assert_eq!(
+        res.stdout,
+        "dir/a.tar.gz dir/a.tar a.tar.gz dir a.tar\nb b b . b\n"
+    );
+    let res = sh
+        .eval_program("parallel --dry-run -k gzip -c {} {.}.gz ::: a.txt b")
+        .unwrap();
+    assert_eq!(res.stdout, "gzip -c a.txt a.gz\ngzip -c b b.gz\n");
+}
+
+#[test]
+fn exit_status_counts_failed_jobs_unless_halting() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir).display().to_string();
+    let mut sh = shell();
+    let res = sh
+        .eval_program(&format!("parallel -j1 {job} 0 {{}} ::: 0 3 4 0"))
+        .unwrap();
+    assert_eq!(res.exit_code, 2);
+    let res = sh
+        .eval_program(&format!(
+            "parallel -j1 --halt soon {job} {{}} {{}} ::: 0 3 4"
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 3);
+    assert_eq!(res.stdout, "0\n3\n");
+}
+
+#[test]
+fn halt_now_terminates_the_running_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir).display().to_string();
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh
+        .eval_program(&format!("parallel -j2 --halt now {job} {{}} 1 ::: 10 0.1"))
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // The jobs leave the table once reported
+    let res = sh.eval_program("jobs").unwrap();
+    assert_eq!(res.stdout, "");
+}
+
+#[test]
+fn missing_commands_are_reported_per_job() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("parallel nxsh-no-such-command ::: a")
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("nxsh-no-such-command"));
+}
+
+#[test]
+fn joblog_records_every_job() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir).display().to_string();
+    let log = dir.path().join("jobs.log");
+    let mut sh = shell();
+    sh.eval_program(&format!(
+        "parallel --joblog {} {job} 0 {{}} ::: 0 5",
+        log.display()
+    ))
+    .unwrap();
+    let log = std::fs::read_to_string(log).unwrap();
+    let mut lines: Vec<&str> = log.lines().collect();
+    assert!(lines.remove(0).starts_with("Seq\tHost\tStarttime"));
+    lines.sort();
+    assert_eq!(lines.len(), 2);
+    let fields: Vec<&str> = lines[1].split('\t').collect();
+    assert_eq!(fields[0], "2");
+    assert_eq!(fields[5], "2");
+    assert_eq!(fields[6], "5");
+    assert_eq!(fields[8], format!("{job} 0 5"));
+}
+
+#[test]
+fn arguments_come_from_stdin_without_separator() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("echo $(seq 1 3 | parallel -k echo n | wc -l)")
+        .unwrap();
+    assert_eq!(res.stdout, "3\n");
+}
+
+#[test]
+fn usage_errors() {
+    let mut sh = shell();
+    let res = sh.eval_program("parallel --halt later echo ::: a").unwrap();
+    assert_eq!(res.exit_code, 2);
+    assert!(res.stderr.contains("--halt"));
+}
+
+#[test]
+fn wait_n_waits_for_the_first_of_the_given_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let job = job(&dir).display().to_string();
+    let mut sh = shell();
+    sh.eval_program(&format!("{job} 0.1 0 > /dev/null &"))
+        .unwrap();
+    sh.eval_program(&format!("{job} 10 0 > /dev/null &"))
+        .unwrap();
+    sh.eval_program(&format!("{job} 0 7 > /dev/null &"))
+        .unwrap();
+    let started = Instant::now();
+    let res = sh.eval_program("wait -n -p pid %1 %2").unwrap();
+    assert_eq!(res.exit_code, 0);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let res = sh.eval_program("echo $pid").unwrap();
+    assert!(res.stdout.trim().parse::<u32>().is_ok(), "{}", res.stdout);
+    let res = sh.eval_program("wait -n %3").unwrap();
+    assert_eq!(res.exit_code, 7);
+    sh.eval_program("kill %2").unwrap();
+    let res = sh.eval_program("wait -n %9").unwrap();
+    assert_eq!(res.exit_code, 127);
+}
@@ -0,0 +1,80 @@
+//! Pipelines stream each stage's output into the next while both run
+#![cfg(unix)]
+mod common;
+use common::shell;
+
+#[test]
+fn endless_producers_stop_when_the_reader_does() {
+    // `yes` never ends on its own: `head` must read it while it runs
+    let mut sh = shell();
+    let res = sh.eval_program("echo $(yes | head -n 3 | wc -l)").unwrap();
+    assert_eq!(res.stdout, "3\n");
+}
+
+#[test]
+fn builtin_output_feeds_external_commands() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("echo $(echo one two | tr a-z A-Z)")
+        .unwrap();
+    assert_eq!(res.stdout, "ONE TWO\n");
+}
+
+#[test]
+fn function_stages_pass_their_input_to_the_commands_they_run() {
+    let mut sh = shell();
+    sh.eval_program("function up() { tr a-z A-Z; }").unwrap();
+    let res = sh.eval_program("echo $(yes | up | head -n 1)").unwrap();
+    assert_eq!(res.stdout, "Y\n");
+    // More than a pipe buffer passes through without stalling
+    let res = sh
+        .eval_program("echo $(seq 1 100000 | up | wc -l)")
+        .unwrap();
+    assert_eq!(res.stdout, "100000\n");
+}
+
+#[test]
+fn status_is_the_last_stage_unless_pipefail() {
+    let mut sh = shell();
+    let res = sh.eval_program("false | true").unwrap();
+    assert_eq!(res.exit_code, 0);
+    sh.context_mut().set_option("pipefail", true).unwrap();
+    let res = sh.eval_program("false | true").unwrap();
+    assert_eq!(res.exit_code, 1);
+}
+
+#[test]
+fn missing_commands_fail_only_their_stage() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("echo $(nxsh-no-such-command | echo after)")
+        .unwrap();
+    assert_eq!(res.stdout, "after\n");
+    let res = sh.eval_program("echo x | nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert!(res.stderr.contains("nxsh-no-such-command"));
+}
+
+#[test]
+fn stages_in_the_shell_run_at_the_same_time() {
+    // Run one after another, `gen` would never finish
+    let mut sh = shell();
+    sh.eval_program("function gen() { yes; }\nfunction one() { head -n 1; }")
+        .unwrap();
+    let res = sh.eval_program("echo $(gen | one)").unwrap();
+    assert_eq!(res.stdout, "y\n");
+    sh.context_mut().set_option("pipefail", true).unwrap();
+    let res = sh.eval_program("gen | one | wc -l").unwrap();
+    assert_eq!(res.exit_code, 141);
+}
+
+#[test]
+fn builtins_writing_to_a_closed_pipe_end_as_if_by_sigpipe() {
+    let mut sh = shell();
+    sh.context_mut().set_option("pipefail", true).unwrap();
+    // More than a pipe buffer holds, so `echo` is still writing when `true`
+    // exits without reading
+    let res = sh.eval_program("echo $(seq 1 30000) | true").unwrap();
+    assert_eq!(res.exit_code, 141);
+    assert_eq!(res.stderr, "");
+}
@@ -0,0 +1,50 @@
+//! Commands registered by plugins run by name, after builtins and before `$PATH`
+mod common;
+use common::shell;
+use nxsh_core::{CompletionSpec, ExecutionResult, PluginCommand, Shell};
+use std::sync::Arc;
+
+fn greet() -> PluginCommand {
+    PluginCommand {
+        name: "nxsh-greet".to_string(),
+        plugin: "greeter@1.0.0".to_string(),
+        synopsis: "Greet someone".to_string(),
+        help: String::new(),
+        completion: CompletionSpec {
+            usage: "nxsh-greet [NAME]...".to_string(),
+            flags: vec![],
+        },
+        handler: Arc::new(|_, args| {
+            let greeting = format!("hi {}\n", args.join(" "));
+            Ok(ExecutionResult::success(0).with_output(greeting.into_bytes()))
+        }),
+    }
+}
+
+#[test]
+fn registered_commands_run_in_later_shells_and_subshells() {
+    let sh = shell();
+    let state = sh.into_state();
+    state.plugin_commands.register(greet());
+
+    let mut sh = Shell::from_state(state);
+    sh.context_mut().clear_global_timeout();
+    let res = sh.eval_program("nxsh-greet plugin world").unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(res.stdout, "hi plugin world\n");
+
+    let res = sh.eval_program("(nxsh-greet inner)").unwrap();
+    assert_eq!(res.stdout, "hi inner\n");
+}
+
+#[test]
+fn unregistered_commands_are_not_found() {
+    let mut sh = shell();
+    let registry = sh.context_mut().plugin_commands.clone();
+    registry.register(greet());
+    assert_eq!(registry.unregister_plugin("greeter@1.0.0"), ["nxsh-greet"]);
+
+    let res = sh.eval_program("nxsh-greet").unwrap();
+    assert_eq!(res.exit_code, 127);
+    assert!(res.stderr.contains("command not found: nxsh-greet"));
+}
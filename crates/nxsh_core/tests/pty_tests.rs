@@ -0,0 +1,64 @@
+//! With `shopt -s pty`, programs run on a pseudo-terminal when the shell has
+//! no terminal to hand them
+#![cfg(unix)]
+mod common;
+use common::script;
+use nxsh_core::Shell;
+
+fn shell() -> Shell {
+    let mut shell = common::shell();
+    shell.context_mut().set_option("pty", true).unwrap();
+    shell
+}
+
+// What the programs see is written to files: their output goes to the
+// test's own stdout, not into the results
+
+#[test]
+fn programs_get_a_terminal_of_their_own() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let check = script(
+        &dir,
+        "check",
+        &format!(
+            "[ -t 0 ] && [ -t 1 ] && [ -t 2 ] && echo terminal > {0}\ntty >> {0}\n",
+            out.display()
+        ),
+    );
+    let mut sh = shell();
+    let res = sh.eval_program(&check.display().to_string()).unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let seen = std::fs::read_to_string(&out).unwrap();
+    assert!(seen.starts_with("terminal\n/dev/"), "{seen}");
+}
+
+#[test]
+fn the_terminal_has_the_shells_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let size = script(&dir, "size", &format!("stty size > {}\n", out.display()));
+    let mut sh = shell();
+    sh.eval_program("COLUMNS=100; LINES=30").unwrap();
+    sh.eval_program(&size.display().to_string()).unwrap();
+    // The test's own terminal wins over the variables when it has one
+    let expected = nxsh_hal::terminal::size().map_or((30, 100), |size| (size.rows, size.columns));
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        format!("{} {}\n", expected.0, expected.1)
+    );
+}
+
+#[test]
+fn exit_status_comes_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let fail = script(&dir, "fail", "[ -t 1 ] || echo plain\nexit 3\n");
+    let fail = fail.display().to_string();
+    let mut sh = shell();
+    assert_eq!(sh.eval_program(&fail).unwrap().exit_code, 3);
+    let res = sh.eval_program("nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+    // Captured output needs no terminal
+    let res = sh.eval_program(&format!("echo $({fail})")).unwrap();
+    assert_eq!(res.stdout, "plain\n");
+}
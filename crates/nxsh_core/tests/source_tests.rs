@@ -0,0 +1,62 @@
+//! `source` / `.` run scripts in the caller's context
+mod common;
+use common::shell_in;
+use std::fs;
+
+#[test]
+fn sourced_assignments_persist() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("env.sh"), "GREETING=hello\necho sourced\n").unwrap();
+    let mut sh = shell_in(&dir);
+
+    let res = sh.eval_program("source ./env.sh\necho $GREETING").unwrap();
+    assert_eq!(res.stdout, "sourced\nhello\n");
+    let res = sh.eval_program(". ./env.sh").unwrap();
+    assert_eq!(res.exit_code, 0);
+}
+
+#[test]
+fn arguments_override_positionals_while_sourcing() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("args.sh"), "echo $# $1 $2\n").unwrap();
+    let mut sh = shell_in(&dir);
+    sh.context().set_positional_args(vec!["outer".to_string()]);
+
+    let res = sh.eval_program("source ./args.sh a b\necho $1").unwrap();
+    assert_eq!(res.stdout, "2 a b\nouter\n");
+}
+
+#[test]
+fn return_leaves_only_the_sourced_file() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("early.sh"),
+        "echo before\nif true; then return 3; fi\necho after\n",
+    )
+    .unwrap();
+    let mut sh = shell_in(&dir);
+
+    let res = sh
+        .eval_program("source ./early.sh\necho status $?")
+        .unwrap();
+    assert_eq!(res.stdout, "before\nstatus 3\n");
+
+    let res = sh.eval_program("return 1").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("can only `return'"));
+}
+
+#[test]
+fn missing_files_and_parse_errors_fail() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("broken.sh"), "if then fi (\n").unwrap();
+    let mut sh = shell_in(&dir);
+
+    let res = sh.eval_program("source ./nope.sh").unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert!(res.stderr.contains("./nope.sh: No such file or directory"));
+    let res = sh.eval_program("source ./broken.sh").unwrap();
+    assert_eq!(res.exit_code, 2);
+    let res = sh.eval_program("source").unwrap();
+    assert_eq!(res.exit_code, 2);
+}
@@ -0,0 +1,106 @@
+//! `timeout` stops the command it runs once its time limit passes
+#![cfg(unix)]
+mod common;
+use common::{script, shell};
+use std::time::{Duration, Instant};
+
+#[test]
+fn programs_past_the_limit_are_stopped_with_status_124() {
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh.eval_program("timeout 0.2 sleep 10").unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert_eq!(res.stderr, "");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn commands_finishing_in_time_keep_their_status() {
+    let mut sh = shell();
+    assert_eq!(sh.eval_program("timeout 5 false").unwrap().exit_code, 1);
+    let res = sh.eval_program("echo $(timeout 5 echo done)").unwrap();
+    assert_eq!(res.stdout, "done\n");
+    // 0 sets no limit
+    assert_eq!(sh.eval_program("timeout 0 true").unwrap().exit_code, 0);
+}
+
+#[test]
+fn preserve_status_reports_the_signal() {
+    let mut sh = shell();
+    let res = sh
+        .eval_program("timeout --preserve-status 0.2 sleep 10")
+        .unwrap();
+    assert_eq!(res.exit_code, 128 + 15);
+    let res = sh
+        .eval_program("timeout --preserve-status -s KILL 0.2 sleep 10")
+        .unwrap();
+    assert_eq!(res.exit_code, 128 + 9);
+}
+
+#[test]
+fn kill_after_escalates_when_the_signal_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let stubborn = script(&dir, "stubborn", "trap '' TERM\nsleep 10\n");
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh
+        .eval_program(&format!("timeout -v -k 0.2 0.2 {}", stubborn.display()))
+        .unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(res.stderr.contains("sending signal TERM"), "{}", res.stderr);
+    assert!(res.stderr.contains("sending signal KILL"), "{}", res.stderr);
+}
+
+#[test]
+fn the_signal_reaches_programs_the_command_started() {
+    // `sleep` would hold the substitution's pipe open if only `sh` ended
+    let dir = tempfile::tempdir().unwrap();
+    let slow = script(&dir, "slow", "echo started\nsleep 10\necho never\n");
+    let mut sh = shell();
+    let started = Instant::now();
+    let res = sh
+        .eval_program(&format!("echo $(timeout 0.3 {})", slow.display()))
+        .unwrap();
+    assert_eq!(res.stdout, "started\n");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn functions_and_builtins_stop_at_the_limit() {
+    let mut sh = shell();
+    sh.eval_program("function slow() { sleep 10; echo late; }")
+        .unwrap();
+    let started = Instant::now();
+    let res = sh.eval_program("timeout 0.3 slow").unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert_eq!(res.stdout, "");
+    assert_eq!(res.stderr, "");
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    sh.eval_program("sleep 10 > /dev/null &").unwrap();
+    let started = Instant::now();
+    let res = sh.eval_program("timeout 0.3 wait").unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    sh.eval_program("kill %1").unwrap();
+    // The limit ends with the command
+    assert_eq!(sh.eval_program("true").unwrap().exit_code, 0);
+}
+
+#[test]
+fn usage_errors_exit_125() {
+    let mut sh = shell();
+    for command in [
+        "timeout",
+        "timeout 1",
+        "timeout soon true",
+        "timeout -s NOPE 1 true",
+    ] {
+        let res = sh.eval_program(command).unwrap();
+        assert_eq!(res.exit_code, 125, "{command}");
+        assert!(res.stderr.contains("timeout: usage"), "{command}");
+    }
+    let res = sh.eval_program("timeout 1 nxsh-no-such-command").unwrap();
+    assert_eq!(res.exit_code, 127);
+}
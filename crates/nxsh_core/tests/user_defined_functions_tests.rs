@@ -51,8 +51,8 @@ fn test_higher_order_functions() {
 
     // Test map function with double
     let map_args = vec![
-        MirValue::String("double".to_string()),
-        MirValue::Array(vec![
+        MirValue::string("double"),
+        MirValue::array(vec![
             MirValue::Integer(1),
             MirValue::Integer(2),
             MirValue::Integer(3),
@@ -64,7 +64,7 @@ fn test_higher_order_functions() {
 
     if let MirValue::Array(arr) = result {
         assert_eq!(
-            arr,
+            *arr,
             vec![
                 MirValue::Integer(2),
                 MirValue::Integer(4),
@@ -78,8 +78,8 @@ fn test_higher_order_functions() {
 
     // Test filter function
     let filter_args = vec![
-        MirValue::String("isEven".to_string()),
-        MirValue::Array(vec![
+        MirValue::string("isEven"),
+        MirValue::array(vec![
             MirValue::Integer(1),
             MirValue::Integer(2),
             MirValue::Integer(3),
@@ -94,7 +94,7 @@ fn test_higher_order_functions() {
 
     if let MirValue::Array(arr) = result {
         assert_eq!(
-            arr,
+            *arr,
             vec![
                 MirValue::Integer(2),
                 MirValue::Integer(4),
@@ -108,8 +108,8 @@ fn test_higher_order_functions() {
 
     // Test reduce function
     let reduce_args = vec![
-        MirValue::String("add".to_string()),
-        MirValue::Array(vec![
+        MirValue::string("add"),
+        MirValue::array(vec![
             MirValue::Integer(1),
             MirValue::Integer(2),
             MirValue::Integer(3),
@@ -133,8 +133,8 @@ fn test_performance_optimized_functions() {
 
     let start = std::time::Instant::now();
     let sum_args = vec![
-        MirValue::String("add".to_string()),
-        MirValue::Array(large_array.clone()),
+        MirValue::string("add"),
+        MirValue::array(large_array.clone()),
     ];
     let result = executor
         .call_user_function_by_name("reduce", sum_args)
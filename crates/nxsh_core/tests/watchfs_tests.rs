@@ -0,0 +1,171 @@
+//! `watchfs` runs a command again whenever the files it watches change
+#![cfg(unix)]
+mod common;
+use common::{script, shell};
+use nxsh_core::Shell;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A directory to watch, and a command that logs each run with the files
+/// it was for. The shell works from an empty directory, where the
+/// patterns it is given are left unexpanded.
+fn setup(sh: &mut Shell) -> (tempfile::TempDir, PathBuf, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let watched = dir.path().join("watched");
+    std::fs::create_dir_all(watched.join("src")).unwrap();
+    std::fs::create_dir(dir.path().join("empty")).unwrap();
+    let log = dir.path().join("log");
+    let record = script(
+        &dir,
+        "record",
+        &format!(
+            "printf 'run %s\\n' \"$WATCHFS_CHANGED\" >> {}\n",
+            log.display()
+        ),
+    );
+    sh.eval_program(&format!("cd {}", dir.path().join("empty").display()))
+        .unwrap();
+    (dir, watched, record.display().to_string())
+}
+
+/// Writes the files, relative to `dir`, once the watching has started
+fn write_later(dir: &Path, files: &[&'static str]) -> thread::JoinHandle<()> {
+    let dir = dir.to_path_buf();
+    let files = files.to_vec();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        for file in files {
+            std::fs::write(dir.join(file), "changed").unwrap();
+        }
+    })
+}
+
+fn log(dir: &tempfile::TempDir) -> String {
+    std::fs::read_to_string(dir.path().join("log")).unwrap_or_default()
+}
+
+#[test]
+fn the_command_runs_first_and_again_after_changes() {
+    let mut sh = shell();
+    let (dir, watched, record) = setup(&mut sh);
+    let writer = write_later(&watched, &["src/main.rs", "notes.txt"]);
+    let res = sh
+        .eval_program(&format!("watchfs -n 1 {} -- {record}", watched.display()))
+        .unwrap();
+    writer.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    let log = log(&dir);
+    let runs: Vec<&str> = log.split("run ").skip(1).collect();
+    assert_eq!(runs.len(), 2, "{log}");
+    assert_eq!(runs[0], "\n");
+    assert!(
+        runs[1].contains(&format!("{}/src/main.rs", watched.display())),
+        "{log}"
+    );
+    assert!(runs[1].contains("notes.txt"), "{log}");
+    // The variable is only there for the command
+    let res = sh.eval_program("echo $WATCHFS_CHANGED end").unwrap();
+    let unset = sh.eval_program("echo $NXSH_NO_SUCH_VARIABLE end").unwrap();
+    assert_eq!(res.stdout, unset.stdout);
+}
+
+#[test]
+fn globs_pick_the_files_and_ignores_drop_them() {
+    let mut sh = shell();
+    let (dir, watched, record) = setup(&mut sh);
+    let writer = write_later(
+        &watched,
+        &[
+            "notes.txt",
+            "src/skipped.rs",
+            "src/.lib.rs.swp",
+            "src/lib.rs",
+        ],
+    );
+    let res = sh
+        .eval_program(&format!(
+            "watchfs -p -n 1 -g *.rs -i *skip* {} -- {record}",
+            watched.display()
+        ))
+        .unwrap();
+    writer.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(log(&dir), format!("run {}/src/lib.rs\n", watched.display()));
+}
+
+#[test]
+fn version_control_and_editor_files_are_ignored() {
+    let mut sh = shell();
+    let (dir, watched, record) = setup(&mut sh);
+    std::fs::create_dir(watched.join(".git")).unwrap();
+    let writer = write_later(
+        &watched,
+        &[".git/index", "src/lib.rs~", "src/.#lib.rs", "src/lib.rs"],
+    );
+    let res = sh
+        .eval_program(&format!(
+            "watchfs -p -n 1 {} -- {record}",
+            watched.display()
+        ))
+        .unwrap();
+    writer.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(log(&dir), format!("run {}/src/lib.rs\n", watched.display()));
+}
+
+#[test]
+fn single_files_can_be_watched() {
+    let mut sh = shell();
+    let (dir, watched, record) = setup(&mut sh);
+    let config = watched.join("config");
+    std::fs::write(&config, "old").unwrap();
+    let writer = write_later(&watched, &["other", "config"]);
+    let res = sh
+        .eval_program(&format!("watchfs -p -n 1 {} -- {record}", config.display()))
+        .unwrap();
+    writer.join().unwrap();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    assert_eq!(log(&dir), format!("run {}\n", config.display()));
+}
+
+#[test]
+fn timeout_ends_the_watching() {
+    let mut sh = shell();
+    let (dir, watched, record) = setup(&mut sh);
+    let res = sh
+        .eval_program(&format!(
+            "timeout 1 watchfs {} -- {record}",
+            watched.display()
+        ))
+        .unwrap();
+    assert_eq!(res.exit_code, 124);
+    assert_eq!(log(&dir), "run \n");
+}
+
+#[test]
+fn usage_errors_exit_2() {
+    let mut sh = shell();
+    for command in [
+        "watchfs",
+        "watchfs . true",
+        "watchfs -- true",
+        "watchfs . --",
+        "watchfs -n none . -- true",
+        "watchfs -d soon . -- true",
+        "watchfs -g",
+        "watchfs --what . -- true",
+    ] {
+        let res = sh.eval_program(command).unwrap();
+        assert_eq!(res.exit_code, 2, "{command}");
+        assert!(res.stderr.contains("watchfs: usage"), "{command}");
+    }
+    let res = sh
+        .eval_program("watchfs nxsh-no-such-path -- true")
+        .unwrap();
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        "nxsh: watchfs: nxsh-no-such-path: No such file or directory\n"
+    );
+}
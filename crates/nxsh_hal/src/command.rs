@@ -3,9 +3,11 @@
 //! This module provides abstractions for command execution across different platforms.
 
 use crate::error::{HalError, HalResult};
-use std::ffi::OsStr;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
 use std::process::{Command as StdCommand, ExitStatus, Stdio};
+use std::time::SystemTime;
 
 /// Command builder and executor
 pub struct Command {
@@ -323,3 +325,434 @@ pub fn command_exists<S: AsRef<OsStr>>(command: S) -> bool {
         }
     }
 }
+
+/// Whether `path` is a file the current user may execute
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Find `name` in the directories of a `PATH`-style list, the way a shell
+/// looks up commands. Names containing a path separator are never searched.
+/// On Windows each `PATHEXT` extension is tried as well.
+pub fn find_in_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    if name.is_empty() || name.contains('/') || (cfg!(windows) && name.contains('\\')) {
+        return None;
+    }
+    #[cfg(windows)]
+    let extensions: Vec<String> = std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    for dir in std::env::split_paths(path) {
+        // An empty PATH entry means the current directory
+        let dir = if dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            dir
+        };
+        let candidate = dir.join(name);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+        #[cfg(windows)]
+        for ext in &extensions {
+            let candidate = dir.join(format!("{name}{ext}"));
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+/// A remembered command location and how often it was used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashEntry {
+    /// Full path of the command
+    pub path: PathBuf,
+    /// Number of times the shell ran the command through this entry
+    pub hits: u32,
+}
+
+/// Remembered locations of external commands, like the `hash` table of
+/// POSIX shells. Every entry belongs to the `PATH` it was found with; a
+/// different `PATH` empties the table. The cache also keeps the index of
+/// every executable on `PATH` used to suggest corrections.
+#[derive(Debug, Clone, Default)]
+pub struct PathCache {
+    path: Option<OsString>,
+    entries: BTreeMap<String, HashEntry>,
+    index: ExecutableIndex,
+}
+
+impl PathCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop every entry when `path` is not the `PATH` the table was built for
+    fn sync_path(&mut self, path: &OsStr) {
+        if self.path.as_deref() != Some(path) {
+            self.entries.clear();
+            self.path = Some(path.to_os_string());
+        }
+    }
+
+    /// Location of `name` for running it, counting a hit. A remembered file
+    /// that is no longer executable is looked up again.
+    pub fn resolve(&mut self, name: &str, path: &OsStr) -> Option<PathBuf> {
+        let found = self.remember(name, path)?;
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.hits += 1;
+        }
+        Some(found)
+    }
+
+    /// Look `name` up and remember it without counting a hit (`hash name`)
+    pub fn remember(&mut self, name: &str, path: &OsStr) -> Option<PathBuf> {
+        self.sync_path(path);
+        if let Some(entry) = self.entries.get(name) {
+            if is_executable(&entry.path) {
+                return Some(entry.path.clone());
+            }
+        }
+        match find_in_path(name, path) {
+            Some(found) => {
+                let hits = self.entries.get(name).map_or(0, |entry| entry.hits);
+                self.entries.insert(
+                    name.to_string(),
+                    HashEntry {
+                        path: found.clone(),
+                        hits,
+                    },
+                );
+                Some(found)
+            }
+            None => {
+                self.entries.remove(name);
+                None
+            }
+        }
+    }
+
+    /// Remember `path` as the location of `name` without searching (`hash -p`)
+    pub fn insert(&mut self, name: &str, path: PathBuf) {
+        self.entries
+            .insert(name.to_string(), HashEntry { path, hits: 0 });
+    }
+
+    /// Forget `name`; false if it was not remembered (`hash -d`)
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// Forget every command (`hash -r`)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    /// Every command name available on `path`, from the executable index
+    pub fn executables(&mut self, path: &OsStr) -> BTreeSet<String> {
+        self.index.names(path)
+    }
+
+    /// Entry for `name`, if remembered
+    pub fn get(&self, name: &str) -> Option<&HashEntry> {
+        self.entries.get(name)
+    }
+
+    /// Remembered commands in name order
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &HashEntry)> {
+        self.entries
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    /// Whether nothing is remembered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Executables found in one `PATH` directory
+#[derive(Debug, Clone)]
+struct IndexedDir {
+    modified: Option<SystemTime>,
+    names: Vec<String>,
+}
+
+/// Names of the executables in the `PATH` directories, for suggesting
+/// corrections to mistyped commands. Each directory is read once and read
+/// again only when its modification time changes.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutableIndex {
+    dirs: HashMap<PathBuf, IndexedDir>,
+}
+
+impl ExecutableIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every command name that `path` makes available, in name order
+    pub fn names(&mut self, path: &OsStr) -> BTreeSet<String> {
+        let mut all = BTreeSet::new();
+        for dir in std::env::split_paths(path) {
+            let dir = if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir
+            };
+            let modified = dir.metadata().and_then(|meta| meta.modified()).ok();
+            let fresh = self
+                .dirs
+                .get(&dir)
+                .is_some_and(|indexed| modified.is_some() && indexed.modified == modified);
+            if !fresh {
+                let names = scan_executables(&dir);
+                self.dirs
+                    .insert(dir.clone(), IndexedDir { modified, names });
+            }
+            if let Some(indexed) = self.dirs.get(&dir) {
+                all.extend(indexed.names.iter().cloned());
+            }
+        }
+        all
+    }
+
+    /// Forget every directory, so the next lookup reads them again
+    pub fn clear(&mut self) {
+        self.dirs.clear();
+    }
+}
+
+/// Command names of the executables directly inside `dir`. On Windows the
+/// `PATHEXT` extension is dropped, as commands are typed without it.
+fn scan_executables(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    #[cfg(windows)]
+    let extensions: Vec<String> = std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if !is_executable(&entry.path()) {
+            continue;
+        }
+        #[cfg(windows)]
+        let name = match name.rfind('.') {
+            Some(dot) if extensions.contains(&name[dot..].to_ascii_lowercase()) => {
+                name[..dot].to_string()
+            }
+            _ => continue,
+        };
+        names.push(name);
+    }
+    names
+}
+
+/// Damerau–Levenshtein distance between `a` and `b` in characters (the
+/// optimal string alignment form): insertions, deletions, substitutions and
+/// swaps of two adjacent characters each count one.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let width = b.len() + 1;
+    let mut d = vec![0usize; (a.len() + 1) * width];
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for (j, cell) in d.iter_mut().enumerate().take(width) {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+    d[a.len() * width + b.len()]
+}
+
+/// Candidates within `max_distance` edits of `name`, closest first and then
+/// in name order, without duplicates or `name` itself
+pub fn closest_matches<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    max_distance: usize,
+) -> Vec<String> {
+    let name_len = name.chars().count();
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        // Lengths alone already rule most candidates out
+        .filter(|candidate| candidate.chars().count().abs_diff(name_len) <= max_distance)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+        .into_iter()
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Shell exit status of a finished command: its exit code, or 128 plus the
+/// signal that killed it. The message is what shells print for a command
+/// killed by a signal, e.g. `Segmentation fault (core dumped)`; interrupts
+/// and broken pipes are silent.
+pub fn describe_exit(status: ExitStatus) -> (i32, Option<String>) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::Signal;
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(sig) = status.signal() {
+            let description = match Signal::try_from(sig) {
+                Ok(Signal::SIGINT | Signal::SIGPIPE) => None,
+                Ok(Signal::SIGHUP) => Some("Hangup".to_string()),
+                Ok(Signal::SIGQUIT) => Some("Quit".to_string()),
+                Ok(Signal::SIGILL) => Some("Illegal instruction".to_string()),
+                Ok(Signal::SIGABRT) => Some("Aborted".to_string()),
+                Ok(Signal::SIGBUS) => Some("Bus error".to_string()),
+                Ok(Signal::SIGFPE) => Some("Floating point exception".to_string()),
+                Ok(Signal::SIGKILL) => Some("Killed".to_string()),
+                Ok(Signal::SIGSEGV) => Some("Segmentation fault".to_string()),
+                Ok(Signal::SIGTERM) => Some("Terminated".to_string()),
+                _ => Some(format!("Signal {sig}")),
+            };
+            let message = description.map(|text| {
+                if status.core_dumped() {
+                    format!("{text} (core dumped)")
+                } else {
+                    text
+                }
+            });
+            return (128 + sig, message);
+        }
+    }
+    (status.code().unwrap_or(1), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn path_lookup_and_cache() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.path().join("data"), "").unwrap();
+        let path =
+            std::env::join_paths(["/nonexistent-dir", dir.path().to_str().unwrap()]).unwrap();
+
+        assert_eq!(find_in_path("tool", &path), Some(tool.clone()));
+        assert_eq!(find_in_path("data", &path), None);
+        assert_eq!(find_in_path("./tool", &path), None);
+
+        let mut cache = PathCache::new();
+        assert_eq!(cache.resolve("tool", &path), Some(tool.clone()));
+        assert_eq!(cache.resolve("tool", &path), Some(tool.clone()));
+        assert_eq!(cache.get("tool").map(|entry| entry.hits), Some(2));
+        assert_eq!(cache.resolve("missing", &path), None);
+        assert!(cache.get("missing").is_none());
+
+        // A new PATH starts a fresh table
+        assert_eq!(cache.resolve("tool", OsStr::new("/nonexistent-dir")), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn distance_counts_adjacent_swaps_once() {
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("dcoker", "docker"), 1);
+        assert_eq!(edit_distance("ls", "cd"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let names = ["git", "gist", "grep", "get", "ls"];
+        assert_eq!(closest_matches("gti", names, 1), vec!["git"]);
+        assert_eq!(closest_matches("gti", names, 2), vec!["git", "get"]);
+        assert!(closest_matches("git", ["git"], 2).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn index_lists_executables_and_notices_new_ones() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let add_tool = |name: &str| {
+            let tool = dir.path().join(name);
+            std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        add_tool("alpha");
+        std::fs::write(dir.path().join("notes"), "").unwrap();
+        let path = dir.path().as_os_str();
+
+        let mut index = ExecutableIndex::new();
+        assert_eq!(index.names(path).into_iter().collect::<Vec<_>>(), ["alpha"]);
+
+        add_tool("beta");
+        // Directory times can be coarse; clearing forces the rescan
+        index.clear();
+        assert_eq!(
+            index.names(path).into_iter().collect::<Vec<_>>(),
+            ["alpha", "beta"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signals_map_to_128_plus_number() {
+        let status = StdCommand::new("sh")
+            .args(["-c", "kill -KILL $$"])
+            .status()
+            .unwrap();
+        assert_eq!(describe_exit(status), (128 + 9, Some("Killed".to_string())));
+        let status = StdCommand::new("sh")
+            .args(["-c", "exit 3"])
+            .status()
+            .unwrap();
+        assert_eq!(describe_exit(status), (3, None));
+    }
+}
@@ -1003,7 +1003,9 @@ impl CompletionEngine {
                             description: if is_dir {
                                 Some("Directory".to_string())
                             } else {
-                                Some("File".to_string())
+                                let class = crate::magic::identify_path(entry.path())
+                                    .map(|magic| magic.class.label());
+                                Some(class.unwrap_or("File").to_string())
                             },
                             score: self.calculate_score(file_prefix, &file_name),
                         });
@@ -0,0 +1,478 @@
+//! Copying file contents the fastest way the platform offers
+//!
+//! [`copy_file`] first clones the file when its file system can share the
+//! blocks, with the `FICLONE` ioctl on Linux (Btrfs, XFS, bcachefs) and
+//! `clonefile` on macOS (APFS). Otherwise the kernel copies it with
+//! `copy_file_range` on Linux and FreeBSD or `CopyFileExW` on Windows, and
+//! only where none of those apply are the contents read and written here.
+//! The holes of a sparse file stay holes, and progress is reported as the
+//! bytes are copied so a caller can show it.
+
+use crate::error::{HalError, HalResult};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Whether to clone files, sharing their blocks, rather than copy them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Reflink {
+    /// Clone where the file system can, copy elsewhere
+    #[default]
+    Auto,
+    /// Clone or fail
+    Always,
+    /// Always copy the contents
+    Never,
+}
+
+/// Whether the copy gets holes where the source has them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sparse {
+    /// Keep the holes of a sparse source
+    #[default]
+    Auto,
+    /// Also make holes of runs of zeros
+    Always,
+    /// Write every byte
+    Never,
+}
+
+/// How a file was copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// Cloned, sharing the blocks of the source
+    Clone,
+    /// Copied by the kernel or the system's copy routine
+    Kernel,
+    /// Read and written here
+    ReadWrite,
+}
+
+/// What a copy did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Copied {
+    pub bytes: u64,
+    pub method: CopyMethod,
+}
+
+/// How many bytes to copy between progress reports
+const CHUNK: usize = 4 << 20;
+
+/// Copy the contents of the file `from` to `to`, creating `to` or
+/// truncating it, and call `progress` with the number of bytes each step
+/// copied. A new `to` gets the permissions of `from`, less the umask.
+pub fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    to: Q,
+    reflink: Reflink,
+    sparse: Sparse,
+    progress: &mut dyn FnMut(u64),
+) -> HalResult<Copied> {
+    let from = from.as_ref();
+    let to = to.as_ref();
+    let meta = fs::metadata(from).map_err(|e| io_error("copy_file", from, e))?;
+    if meta.is_dir() {
+        return Err(HalError::invalid("is a directory"));
+    }
+    #[cfg(unix)]
+    {
+        unix::copy(from, to, &meta, reflink, sparse, progress)
+    }
+    #[cfg(windows)]
+    {
+        let _ = sparse;
+        windows::copy(from, to, &meta, reflink, progress)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = sparse;
+        if reflink == Reflink::Always {
+            return Err(HalError::unsupported("cloning files is not supported here"));
+        }
+        let mut source = fs::File::open(from).map_err(|e| io_error("open", from, e))?;
+        let mut target = fs::File::create(to).map_err(|e| io_error("create", to, e))?;
+        let bytes = io::copy(&mut source, &mut target).map_err(|e| io_error("copy", to, e))?;
+        progress(bytes);
+        Ok(Copied {
+            bytes,
+            method: CopyMethod::ReadWrite,
+        })
+    }
+}
+
+fn io_error(operation: &str, path: &Path, err: io::Error) -> HalError {
+    HalError::io_error(operation, Some(&path.to_string_lossy()), err)
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{io_error, Copied, CopyMethod, Reflink, Sparse, CHUNK};
+    use crate::error::HalResult;
+    use nix::libc;
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::{FileExt, MetadataExt, OpenOptionsExt};
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    pub(super) fn copy(
+        from: &Path,
+        to: &Path,
+        meta: &fs::Metadata,
+        reflink: Reflink,
+        sparse: Sparse,
+        progress: &mut dyn FnMut(u64),
+    ) -> HalResult<Copied> {
+        let len = meta.len();
+
+        #[cfg(target_os = "macos")]
+        if reflink != Reflink::Never {
+            // clonefile makes the file itself, so only a new one is cloned
+            if reflink == Reflink::Always || fs::symlink_metadata(to).is_err() {
+                match clone_file(from, to) {
+                    Ok(()) => {
+                        progress(len);
+                        return Ok(Copied {
+                            bytes: len,
+                            method: CopyMethod::Clone,
+                        });
+                    }
+                    Err(e) if reflink == Reflink::Always => {
+                        return Err(io_error("clonefile", to, e))
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        let source = fs::File::open(from).map_err(|e| io_error("open", from, e))?;
+        let target = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(meta.mode() & 0o7777)
+            .open(to)
+            .map_err(|e| io_error("create", to, e))?;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if reflink != Reflink::Never {
+            // _IOW(0x94, 9, int)
+            const FICLONE: u32 = 0x4004_9409;
+            let cloned =
+                unsafe { libc::ioctl(target.as_raw_fd(), FICLONE as _, source.as_raw_fd()) };
+            if cloned == 0 {
+                progress(len);
+                return Ok(Copied {
+                    bytes: len,
+                    method: CopyMethod::Clone,
+                });
+            }
+            if reflink == Reflink::Always {
+                return Err(io_error("clone", to, io::Error::last_os_error()));
+            }
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+        if reflink == Reflink::Always {
+            return Err(crate::error::HalError::unsupported(
+                "cloning files is not supported here",
+            ));
+        }
+
+        let mut copy = Copy {
+            source: &source,
+            target: &target,
+            from,
+            to,
+            kernel: true,
+            progress,
+        };
+        // Fewer blocks than the length needs means there are holes
+        let has_holes = meta.blocks().saturating_mul(512) < len;
+        match sparse {
+            Sparse::Auto if has_holes => copy.data_of(len)?,
+            Sparse::Always => {
+                copy.kernel = false;
+                copy.range(0, len, true)?;
+            }
+            _ => copy.range(0, len, false)?,
+        }
+        // What follows the last data is a hole too
+        target
+            .set_len(len)
+            .map_err(|e| io_error("truncate", to, e))?;
+        let method = if copy.kernel {
+            CopyMethod::Kernel
+        } else {
+            CopyMethod::ReadWrite
+        };
+        Ok(Copied { bytes: len, method })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn clone_file(from: &Path, to: &Path) -> io::Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        let c = |path: &Path| {
+            CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+        };
+        let (from, to) = (c(from)?, c(to)?);
+        if unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// One file's copy
+    struct Copy<'a> {
+        source: &'a fs::File,
+        target: &'a fs::File,
+        from: &'a Path,
+        to: &'a Path,
+        /// Whether the kernel has copied everything so far
+        kernel: bool,
+        progress: &'a mut dyn FnMut(u64),
+    }
+
+    impl Copy<'_> {
+        /// Copy only the data of a sparse source, skipping its holes
+        fn data_of(&mut self, len: u64) -> HalResult<()> {
+            let fd = self.source.as_raw_fd();
+            let mut at = 0u64;
+            while at < len {
+                let start = unsafe { libc::lseek(fd, at as libc::off_t, libc::SEEK_DATA) };
+                if start < 0 {
+                    let err = io::Error::last_os_error();
+                    return match err.raw_os_error() {
+                        // No data after `at`
+                        Some(libc::ENXIO) => Ok(()),
+                        // Holes cannot be found here: copy the rest whole
+                        Some(libc::EINVAL) | Some(libc::ENOTSUP) if at == 0 => {
+                            self.range(0, len, false)
+                        }
+                        _ => Err(io_error("seek", self.from, err)),
+                    };
+                }
+                let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+                let end = if end < 0 { len } else { (end as u64).min(len) };
+                self.range(start as u64, end, false)?;
+                at = end;
+            }
+            Ok(())
+        }
+
+        /// Copy the bytes from `start` to `end`, leaving holes for runs of
+        /// zeros when `holes` says so
+        fn range(&mut self, start: u64, end: u64, holes: bool) -> HalResult<()> {
+            let mut at = start;
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+            if self.kernel {
+                while at < end {
+                    let mut off_in = at as libc::off_t;
+                    let mut off_out = at as libc::off_t;
+                    let want = ((end - at) as usize).min(CHUNK);
+                    let copied = unsafe {
+                        libc::copy_file_range(
+                            self.source.as_raw_fd(),
+                            &mut off_in,
+                            self.target.as_raw_fd(),
+                            &mut off_out,
+                            want,
+                            0,
+                        )
+                    };
+                    if copied > 0 {
+                        at += copied as u64;
+                        (self.progress)(copied as u64);
+                        continue;
+                    }
+                    if copied == 0 {
+                        // The source got shorter
+                        return Ok(());
+                    }
+                    let err = io::Error::last_os_error();
+                    match err.raw_os_error() {
+                        Some(libc::EINTR) => continue,
+                        // Across file systems, or not for these files:
+                        // read and write the rest instead
+                        Some(
+                            libc::EXDEV
+                            | libc::ENOSYS
+                            | libc::EOPNOTSUPP
+                            | libc::EINVAL
+                            | libc::EPERM
+                            | libc::EBADF,
+                        ) => break,
+                        _ => return Err(io_error("copy_file_range", self.to, err)),
+                    }
+                }
+            }
+            if at < end {
+                self.kernel = false;
+            }
+
+            let mut buffer = vec![0u8; CHUNK.min((end - at) as usize)];
+            while at < end {
+                let want = ((end - at) as usize).min(buffer.len());
+                let read = self
+                    .source
+                    .read_at(&mut buffer[..want], at)
+                    .map_err(|e| io_error("read", self.from, e))?;
+                if read == 0 {
+                    return Ok(());
+                }
+                let block = &buffer[..read];
+                if !(holes && block.iter().all(|&b| b == 0)) {
+                    self.target
+                        .write_all_at(block, at)
+                        .map_err(|e| io_error("write", self.to, e))?;
+                }
+                at += read as u64;
+                (self.progress)(read as u64);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::{io_error, Copied, CopyMethod, Reflink};
+    use crate::error::{HalError, HalResult};
+    use std::ffi::c_void;
+    use std::fs;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::Storage::FileSystem::CopyFileExW;
+
+    const PROGRESS_CONTINUE: u32 = 0;
+
+    /// What the progress routine reports to
+    struct Reporter<'a> {
+        progress: &'a mut dyn FnMut(u64),
+        reported: u64,
+    }
+
+    unsafe extern "system" fn report(
+        _total: i64,
+        transferred: i64,
+        _stream_size: i64,
+        _stream_transferred: i64,
+        _stream: u32,
+        _reason: u32,
+        _source: HANDLE,
+        _target: HANDLE,
+        data: *const c_void,
+    ) -> u32 {
+        let reporter = &mut *(data as *mut Reporter);
+        let transferred = transferred as u64;
+        if transferred > reporter.reported {
+            (reporter.progress)(transferred - reporter.reported);
+            reporter.reported = transferred;
+        }
+        PROGRESS_CONTINUE
+    }
+
+    pub(super) fn copy(
+        from: &Path,
+        to: &Path,
+        meta: &fs::Metadata,
+        reflink: Reflink,
+        progress: &mut dyn FnMut(u64),
+    ) -> HalResult<Copied> {
+        // ReFS and Dev Drive volumes clone blocks inside CopyFileEx on
+        // their own; there is no asking for it
+        if reflink == Reflink::Always {
+            return Err(HalError::unsupported("cloning files is not supported here"));
+        }
+        let wide =
+            |path: &Path| -> Vec<u16> { path.as_os_str().encode_wide().chain(Some(0)).collect() };
+        let mut reporter = Reporter {
+            progress,
+            reported: 0,
+        };
+        let copied = unsafe {
+            CopyFileExW(
+                wide(from).as_ptr(),
+                wide(to).as_ptr(),
+                Some(report),
+                &mut reporter as *mut Reporter as *const c_void,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if copied == 0 {
+            return Err(io_error("CopyFileEx", to, io::Error::last_os_error()));
+        }
+        Ok(Copied {
+            bytes: meta.len(),
+            method: CopyMethod::Kernel,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_contents_reporting_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        let to = dir.path().join("to");
+        let data: Vec<u8> = (0..(9 << 20)).map(|i| (i % 251) as u8).collect();
+        fs::write(&from, &data).unwrap();
+        fs::write(&to, b"longer than nothing").unwrap();
+
+        for reflink in [Reflink::Auto, Reflink::Never] {
+            let mut reported = Vec::new();
+            let copied =
+                copy_file(&from, &to, reflink, Sparse::Auto, &mut |n| reported.push(n)).unwrap();
+            assert_eq!(copied.bytes, data.len() as u64);
+            assert_eq!(reported.iter().sum::<u64>(), data.len() as u64);
+            assert_eq!(fs::read(&to).unwrap(), data);
+            if reflink == Reflink::Never {
+                assert_ne!(copied.method, CopyMethod::Clone);
+            }
+        }
+        assert!(copy_file(dir.path(), &to, Reflink::Auto, Sparse::Auto, &mut |_| {}).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_holes() {
+        use std::io::{Seek, SeekFrom, Write};
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("sparse");
+        let to = dir.path().join("copy");
+        let mut file = fs::File::create(&from).unwrap();
+        file.write_all(b"head").unwrap();
+        file.seek(SeekFrom::Start(64 << 20)).unwrap();
+        file.write_all(b"tail").unwrap();
+        drop(file);
+        let len = (64 << 20) + 4;
+        if fs::metadata(&from).unwrap().blocks() * 512 >= len {
+            return; // This file system stores no holes
+        }
+
+        let copied = copy_file(&from, &to, Reflink::Never, Sparse::Auto, &mut |_| {}).unwrap();
+        assert_eq!(copied.bytes, len);
+        let meta = fs::metadata(&to).unwrap();
+        assert_eq!(meta.len(), len);
+        assert!(meta.blocks() * 512 < 1 << 20, "{} blocks", meta.blocks());
+        let data = fs::read(&to).unwrap();
+        assert_eq!(
+            (&data[..4], &data[data.len() - 4..]),
+            (&b"head"[..], &b"tail"[..])
+        );
+
+        // Never writes the zeros out
+        copy_file(&from, &to, Reflink::Never, Sparse::Never, &mut |_| {}).unwrap();
+        assert!(fs::metadata(&to).unwrap().blocks() * 512 >= len);
+    }
+}
@@ -3,11 +3,11 @@
 //! This module provides a comprehensive, platform-agnostic interface to
 //! file system operations with optimizations for each supported platform.
 
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::fs::{self, File, Permissions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
-use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::error::{HalError, HalResult};
 use crate::platform::{Capabilities, Platform};
@@ -61,6 +61,20 @@ impl FileSystem {
         Ok(())
     }
 
+    /// Create a single directory, failing if it already exists. On Unix the
+    /// directory gets `mode` before the umask; elsewhere `mode` is ignored.
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P, mode: u32) -> HalResult<()> {
+        let path = path.as_ref();
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, mode);
+        #[cfg(not(unix))]
+        let _ = mode;
+        builder.create(path).map_err(|e| {
+            HalError::io_error("create_dir", Some(path.to_str().unwrap_or("<invalid>")), e)
+        })
+    }
+
     /// Remove a file
     pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> HalResult<()> {
         let path = path.as_ref();
@@ -130,6 +144,20 @@ impl FileSystem {
         Ok(FileMetadata::from_std(metadata, path))
     }
 
+    /// Get file metadata without following a final symbolic link
+    pub fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> HalResult<FileMetadata> {
+        let path = path.as_ref();
+        let metadata = fs::symlink_metadata(path).map_err(|e| {
+            HalError::io_error(
+                "symlink_metadata",
+                Some(path.to_str().unwrap_or("<invalid>")),
+                e,
+            )
+        })?;
+
+        Ok(FileMetadata::from_std(metadata, path))
+    }
+
     /// Check if a path exists
     pub fn exists<P: AsRef<Path>>(&self, path: P) -> HalResult<bool> {
         let path = path.as_ref();
@@ -211,6 +239,61 @@ impl FileSystem {
         })
     }
 
+    /// Resolve an absolute path the way `realpath` does, dropping `.` and `..`
+    /// and following every symbolic link one component at a time. Unlike
+    /// [`canonicalize`](Self::canonicalize), Windows results keep their plain
+    /// drive letter form rather than a `\\?\` prefix, and `mode` decides how
+    /// much of the path has to exist.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P, mode: ResolveMode) -> HalResult<PathBuf> {
+        let path = path.as_ref();
+        let fail = |e: io::Error| {
+            HalError::io_error("resolve", Some(path.to_str().unwrap_or("<invalid>")), e)
+        };
+        if !path.has_root() {
+            return Err(fail(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path is not absolute",
+            )));
+        }
+
+        let mut resolved = PathBuf::new();
+        let mut pending = Vec::new();
+        queue_components(path, &mut resolved, &mut pending);
+        let mut links = 0;
+        while let Some(name) = pending.pop() {
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+            let candidate = resolved.join(&name);
+            let last = pending.is_empty();
+            match fs::symlink_metadata(&candidate) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    links += 1;
+                    if links > MAX_SYMLINKS {
+                        return Err(fail(io::Error::other("Too many levels of symbolic links")));
+                    }
+                    let target = fs::read_link(&candidate).map_err(fail)?;
+                    queue_components(&target, &mut resolved, &mut pending);
+                }
+                Ok(metadata) if !last && !metadata.is_dir() && mode != ResolveMode::Missing => {
+                    return Err(fail(io::Error::other("Not a directory")));
+                }
+                Ok(_) => resolved = candidate,
+                Err(e)
+                    if mode == ResolveMode::Missing
+                        || (mode == ResolveMode::Parents
+                            && last
+                            && e.kind() == io::ErrorKind::NotFound) =>
+                {
+                    resolved = candidate
+                }
+                Err(e) => return Err(fail(e)),
+            }
+        }
+        Ok(resolved)
+    }
+
     /// Set file permissions
     pub fn set_permissions<P: AsRef<Path>>(
         &self,
@@ -730,6 +813,40 @@ impl DirectoryHandle {
     }
 }
 
+/// How much of a path has to exist for [`FileSystem::resolve`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveMode {
+    /// Every component must exist
+    Existing,
+    /// Every component but the last must exist
+    Parents,
+    /// Missing components are kept as they are written
+    Missing,
+}
+
+/// Symbolic links [`FileSystem::resolve`] follows before giving up on a loop
+const MAX_SYMLINKS: usize = 40;
+
+/// Queue the components of `path` for [`FileSystem::resolve`], last on the
+/// bottom, starting over from its root when it has one
+fn queue_components(path: &Path, resolved: &mut PathBuf, pending: &mut Vec<OsString>) {
+    if path.has_root() {
+        *resolved = path
+            .components()
+            .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+            .collect();
+    }
+    let names: Vec<OsString> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_os_string()),
+            Component::ParentDir => Some(OsString::from("..")),
+            _ => None,
+        })
+        .collect();
+    pending.extend(names.into_iter().rev());
+}
+
 /// Enhanced file metadata
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
@@ -758,6 +875,9 @@ pub struct FileMetadata {
     pub blocks: u64,
     #[cfg(unix)]
     pub block_size: u64,
+    /// Last status change (`st_ctime`)
+    #[cfg(unix)]
+    pub changed: Option<SystemTime>,
 }
 
 impl FileMetadata {
@@ -783,6 +903,10 @@ impl FileMetadata {
                 nlink: metadata.nlink(),
                 blocks: metadata.blocks(),
                 block_size: metadata.blksize(),
+                changed: u64::try_from(metadata.ctime()).ok().map(|secs| {
+                    SystemTime::UNIX_EPOCH
+                        + std::time::Duration::new(secs, metadata.ctime_nsec() as u32)
+                }),
             }
         }
 
@@ -978,6 +1102,985 @@ impl DiskUsage {
     }
 }
 
+/// A change to a file watched by a [`FileWatcher`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// The file appeared
+    Created(PathBuf),
+    /// The file's contents or metadata changed, or it was replaced
+    Modified(PathBuf),
+    /// The file went away
+    Removed(PathBuf),
+}
+
+impl FileChange {
+    /// The file that changed
+    pub fn path(&self) -> &Path {
+        match self {
+            FileChange::Created(path) | FileChange::Modified(path) | FileChange::Removed(path) => {
+                path
+            }
+        }
+    }
+}
+
+/// Watches files for changes by polling their metadata
+///
+/// Polling behaves the same on every platform and file system, network
+/// mounts included. A change is reported once the file has looked the same
+/// for a whole poll interval, so a file that is still being written, or is
+/// replaced in several steps, is reported once and only when it is done.
+/// Files need not exist to be watched: one that appears is `Created`.
+#[derive(Debug)]
+pub struct FileWatcher {
+    interval: Duration,
+    files: Vec<WatchedFile>,
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: PathBuf,
+    /// What the file looked like when it last changed, as reported
+    reported: Option<FileStamp>,
+    /// What the file looked like at the last poll
+    observed: Option<FileStamp>,
+}
+
+/// What is compared between polls: a file rewritten in place changes its
+/// modification time or size, one replaced by a rename its inode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    inode: u64,
+}
+
+impl FileStamp {
+    pub(crate) fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        #[cfg(unix)]
+        let inode = {
+            use std::os::unix::fs::MetadataExt;
+            metadata.ino()
+        };
+        #[cfg(not(unix))]
+        let inode = 0;
+        Some(FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            inode,
+        })
+    }
+}
+
+impl FileWatcher {
+    /// Create a watcher polling every `interval`
+    pub fn new(interval: Duration) -> Self {
+        FileWatcher {
+            interval,
+            files: Vec::new(),
+        }
+    }
+
+    /// How often files are polled
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Start watching `path`; changes are reported from how it looks now
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if self.files.iter().any(|file| file.path == path) {
+            return;
+        }
+        let stamp = FileStamp::of(path);
+        self.files.push(WatchedFile {
+            path: path.to_path_buf(),
+            reported: stamp,
+            observed: stamp,
+        });
+    }
+
+    /// Stop watching `path`
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.files.retain(|file| file.path != path);
+    }
+
+    /// The files being watched
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+
+    /// Check the watched files once, returning the changes that have
+    /// settled since the last check
+    pub fn poll(&mut self) -> Vec<FileChange> {
+        let mut changes = Vec::new();
+        for file in &mut self.files {
+            let stamp = FileStamp::of(&file.path);
+            if stamp != file.observed {
+                // Still changing: report it when it has settled
+                file.observed = stamp;
+                continue;
+            }
+            if stamp == file.reported {
+                continue;
+            }
+            let path = file.path.clone();
+            changes.push(match (file.reported, stamp) {
+                (None, _) => FileChange::Created(path),
+                (_, None) => FileChange::Removed(path),
+                _ => FileChange::Modified(path),
+            });
+            file.reported = stamp;
+        }
+        changes
+    }
+
+    /// Poll until a change settles or `timeout` passes, returning the
+    /// changes, or none on timeout; without a timeout, wait for a change
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Vec<FileChange> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let changes = self.poll();
+            if !changes.is_empty() {
+                return changes;
+            }
+            let pause = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return changes;
+                    }
+                    left.min(self.interval)
+                }
+                None => self.interval,
+            };
+            std::thread::sleep(pause);
+        }
+    }
+}
+
+/// The extended attribute names of `path`: `user.*` and the other
+/// namespaces on Linux, any name on macOS. With `follow` a symbolic link is
+/// followed, otherwise the attributes are the link's own.
+pub fn list_xattrs<P: AsRef<Path>>(path: P, follow: bool) -> HalResult<Vec<OsString>> {
+    let path = path.as_ref();
+    let names = xattr::list(path, follow).map_err(|e| xattr_error("listxattr", path, e))?;
+    Ok(names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(xattr::name_from_bytes)
+        .collect())
+}
+
+/// The value of the extended attribute `name` of `path`, or `None` when
+/// the file has no such attribute
+pub fn get_xattr<P: AsRef<Path>, N: AsRef<OsStr>>(
+    path: P,
+    name: N,
+    follow: bool,
+) -> HalResult<Option<Vec<u8>>> {
+    let path = path.as_ref();
+    xattr::get(path, name.as_ref(), follow).map_err(|e| xattr_error("getxattr", path, e))
+}
+
+/// Set the extended attribute `name` of `path` to `value`, creating it or
+/// replacing the value it had
+pub fn set_xattr<P: AsRef<Path>, N: AsRef<OsStr>>(
+    path: P,
+    name: N,
+    value: &[u8],
+    follow: bool,
+) -> HalResult<()> {
+    let path = path.as_ref();
+    xattr::set(path, name.as_ref(), value, follow).map_err(|e| xattr_error("setxattr", path, e))
+}
+
+/// Remove the extended attribute `name` of `path`
+pub fn remove_xattr<P: AsRef<Path>, N: AsRef<OsStr>>(
+    path: P,
+    name: N,
+    follow: bool,
+) -> HalResult<()> {
+    let path = path.as_ref();
+    xattr::remove(path, name.as_ref(), follow).map_err(|e| xattr_error("removexattr", path, e))
+}
+
+fn xattr_error(operation: &str, path: &Path, err: io::Error) -> HalError {
+    if err.kind() == io::ErrorKind::Unsupported {
+        return HalError::unsupported(&format!(
+            "{}: extended attributes are not supported here",
+            path.display()
+        ));
+    }
+    HalError::io_error(operation, Some(&path.display().to_string()), err)
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+mod xattr {
+    use nix::libc::{self, c_char, c_void, size_t, ssize_t};
+    use std::ffi::{CString, OsStr, OsString};
+    use std::io;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const NO_ATTRIBUTE: i32 = libc::ENODATA;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    const NO_ATTRIBUTE: i32 = libc::ENOATTR;
+
+    pub(super) fn name_from_bytes(name: &[u8]) -> OsString {
+        OsString::from_vec(name.to_vec())
+    }
+
+    fn c_string(text: &OsStr) -> io::Result<CString> {
+        CString::new(text.as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// The error of a call, with the file systems that keep no attributes
+    /// told apart
+    fn last_error() -> io::Error {
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            // EOPNOTSUPP as well on Linux, where the two are the same
+            Some(libc::ENOTSUP) => io::ErrorKind::Unsupported.into(),
+            _ => err,
+        }
+    }
+
+    /// Read a value of unknown size: ask for the size, then read it into a
+    /// buffer that large, asking again if it grew in between
+    fn read_sized(mut call: impl FnMut(*mut c_void, size_t) -> ssize_t) -> io::Result<Vec<u8>> {
+        loop {
+            let size = call(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(last_error());
+            }
+            let mut buf = vec![0u8; size as usize];
+            let read = call(buf.as_mut_ptr().cast(), buf.len());
+            if read >= 0 {
+                buf.truncate(read as usize);
+                return Ok(buf);
+            }
+            if io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+                return Err(last_error());
+            }
+        }
+    }
+
+    pub(super) fn list(path: &Path, follow: bool) -> io::Result<Vec<u8>> {
+        let path = c_string(path.as_os_str())?;
+        read_sized(|buf, size| unsafe { sys::list(path.as_ptr(), buf.cast(), size, follow) })
+    }
+
+    pub(super) fn get(path: &Path, name: &OsStr, follow: bool) -> io::Result<Option<Vec<u8>>> {
+        let (path, name) = (c_string(path.as_os_str())?, c_string(name)?);
+        match read_sized(|buf, size| unsafe {
+            sys::get(path.as_ptr(), name.as_ptr(), buf, size, follow)
+        }) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.raw_os_error() == Some(NO_ATTRIBUTE) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(super) fn set(path: &Path, name: &OsStr, value: &[u8], follow: bool) -> io::Result<()> {
+        let (path, name) = (c_string(path.as_os_str())?, c_string(name)?);
+        let result = unsafe {
+            sys::set(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                follow,
+            )
+        };
+        if result != 0 {
+            return Err(last_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn remove(path: &Path, name: &OsStr, follow: bool) -> io::Result<()> {
+        let (path, name) = (c_string(path.as_os_str())?, c_string(name)?);
+        if unsafe { sys::remove(path.as_ptr(), name.as_ptr(), follow) } != 0 {
+            return Err(last_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod sys {
+        use super::*;
+
+        pub unsafe fn list(
+            path: *const c_char,
+            buf: *mut c_char,
+            size: size_t,
+            follow: bool,
+        ) -> ssize_t {
+            if follow {
+                libc::listxattr(path, buf, size)
+            } else {
+                libc::llistxattr(path, buf, size)
+            }
+        }
+
+        pub unsafe fn get(
+            path: *const c_char,
+            name: *const c_char,
+            buf: *mut c_void,
+            size: size_t,
+            follow: bool,
+        ) -> ssize_t {
+            if follow {
+                libc::getxattr(path, name, buf, size)
+            } else {
+                libc::lgetxattr(path, name, buf, size)
+            }
+        }
+
+        pub unsafe fn set(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: size_t,
+            follow: bool,
+        ) -> i32 {
+            if follow {
+                libc::setxattr(path, name, value, size, 0)
+            } else {
+                libc::lsetxattr(path, name, value, size, 0)
+            }
+        }
+
+        pub unsafe fn remove(path: *const c_char, name: *const c_char, follow: bool) -> i32 {
+            if follow {
+                libc::removexattr(path, name)
+            } else {
+                libc::lremovexattr(path, name)
+            }
+        }
+    }
+
+    // The calls take the position within resource forks, and whether to
+    // follow links as an option
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    mod sys {
+        use super::*;
+
+        fn options(follow: bool) -> libc::c_int {
+            if follow {
+                0
+            } else {
+                libc::XATTR_NOFOLLOW
+            }
+        }
+
+        pub unsafe fn list(
+            path: *const c_char,
+            buf: *mut c_char,
+            size: size_t,
+            follow: bool,
+        ) -> ssize_t {
+            libc::listxattr(path, buf, size, options(follow))
+        }
+
+        pub unsafe fn get(
+            path: *const c_char,
+            name: *const c_char,
+            buf: *mut c_void,
+            size: size_t,
+            follow: bool,
+        ) -> ssize_t {
+            libc::getxattr(path, name, buf, size, 0, options(follow))
+        }
+
+        pub unsafe fn set(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: size_t,
+            follow: bool,
+        ) -> i32 {
+            libc::setxattr(path, name, value, size, 0, options(follow))
+        }
+
+        pub unsafe fn remove(path: *const c_char, name: *const c_char, follow: bool) -> i32 {
+            libc::removexattr(path, name, options(follow))
+        }
+    }
+}
+
+// Windows keeps named data in alternate streams instead, see
+// `alternate_streams`
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+mod xattr {
+    use std::ffi::{OsStr, OsString};
+    use std::io;
+    use std::path::Path;
+
+    pub(super) fn name_from_bytes(name: &[u8]) -> OsString {
+        OsString::from(String::from_utf8_lossy(name).into_owned())
+    }
+
+    pub(super) fn list(_path: &Path, _follow: bool) -> io::Result<Vec<u8>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn get(_path: &Path, _name: &OsStr, _follow: bool) -> io::Result<Option<Vec<u8>>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn set(_path: &Path, _name: &OsStr, _value: &[u8], _follow: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn remove(_path: &Path, _name: &OsStr, _follow: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// The access control list of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Acl {
+    /// A POSIX.1e list. A file without one of its own gets the three
+    /// entries its mode bits stand for.
+    Posix {
+        access: Vec<PosixAclEntry>,
+        /// What new files in a directory inherit
+        default: Vec<PosixAclEntry>,
+    },
+    /// The discretionary list of a Windows security descriptor, in the
+    /// order the entries are checked in
+    Windows {
+        owner: Option<String>,
+        entries: Vec<WindowsAce>,
+    },
+}
+
+impl Acl {
+    /// Whether the list grants or denies more than the mode bits or the
+    /// inherited entries already say
+    pub fn is_extended(&self) -> bool {
+        match self {
+            Acl::Posix { access, default } => access.len() > 3 || !default.is_empty(),
+            Acl::Windows { entries, .. } => entries.iter().any(|ace| !ace.is_inherited()),
+        }
+    }
+}
+
+/// Whom a POSIX ACL entry is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PosixAclTag {
+    /// The file's owner
+    UserObj,
+    /// The user with the entry's id
+    User,
+    /// The file's group
+    GroupObj,
+    /// The group with the entry's id
+    Group,
+    /// The most the named users and the groups get
+    Mask,
+    Other,
+}
+
+/// One entry of a POSIX ACL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PosixAclEntry {
+    pub tag: PosixAclTag,
+    /// The uid or gid of a `User` or `Group` entry
+    pub id: Option<u32>,
+    /// Read, write and execute as the bits 4, 2 and 1
+    pub permissions: u8,
+}
+
+impl PosixAclEntry {
+    /// The permissions as `getfacl` shows them, like `r-x`
+    pub fn permission_string(&self) -> String {
+        [(4, 'r'), (2, 'w'), (1, 'x')]
+            .iter()
+            .map(|&(bit, c)| if self.permissions & bit != 0 { c } else { '-' })
+            .collect()
+    }
+
+    /// The entries the mode bits of a file stand for
+    pub fn from_mode(mode: u32) -> Vec<PosixAclEntry> {
+        let entry = |tag, shift: u32| PosixAclEntry {
+            tag,
+            id: None,
+            permissions: ((mode >> shift) & 7) as u8,
+        };
+        vec![
+            entry(PosixAclTag::UserObj, 6),
+            entry(PosixAclTag::GroupObj, 3),
+            entry(PosixAclTag::Other, 0),
+        ]
+    }
+
+    /// Decode an ACL kept in the `system.posix_acl_*` attributes on Linux
+    pub fn parse_linux(value: &[u8]) -> HalResult<Vec<PosixAclEntry>> {
+        const VERSION: u32 = 2;
+        let invalid = || HalError::invalid("malformed POSIX ACL attribute");
+        let (header, body) = value.split_at_checked(4).ok_or_else(invalid)?;
+        if u32::from_le_bytes(header.try_into().unwrap()) != VERSION || body.len() % 8 != 0 {
+            return Err(invalid());
+        }
+        body.chunks_exact(8)
+            .map(|entry| {
+                let tag = u16::from_le_bytes([entry[0], entry[1]]);
+                let permissions = u16::from_le_bytes([entry[2], entry[3]]) as u8 & 7;
+                let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+                let tag = match tag {
+                    0x01 => PosixAclTag::UserObj,
+                    0x02 => PosixAclTag::User,
+                    0x04 => PosixAclTag::GroupObj,
+                    0x08 => PosixAclTag::Group,
+                    0x10 => PosixAclTag::Mask,
+                    0x20 => PosixAclTag::Other,
+                    _ => return Err(invalid()),
+                };
+                let id = matches!(tag, PosixAclTag::User | PosixAclTag::Group).then_some(id);
+                Ok(PosixAclEntry {
+                    tag,
+                    id,
+                    permissions,
+                })
+            })
+            .collect()
+    }
+}
+
+/// One access control entry of a Windows ACL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowsAce {
+    /// The account, as `DOMAIN\name`, or its SID when it has no name
+    pub trustee: String,
+    /// Whether the entry allows the access rather than denies it
+    pub allow: bool,
+    /// The access mask
+    pub mask: u32,
+    /// The `ACE_HEADER` flags, inheritance among them
+    pub flags: u8,
+}
+
+impl WindowsAce {
+    const OBJECT_INHERIT: u8 = 0x01;
+    const CONTAINER_INHERIT: u8 = 0x02;
+    const NO_PROPAGATE: u8 = 0x04;
+    const INHERIT_ONLY: u8 = 0x08;
+    const INHERITED: u8 = 0x10;
+
+    /// Whether the entry came from a parent directory
+    pub fn is_inherited(&self) -> bool {
+        self.flags & Self::INHERITED != 0
+    }
+
+    /// The rights the way `icacls` shows them: `F`, `M`, `RX`, `R` or `W`
+    /// for the usual sets, the mask in hex otherwise, followed by the
+    /// inheritance flags like `(OI)(CI)`
+    pub fn rights(&self) -> String {
+        const GENERIC_ALL: u32 = 0x1000_0000;
+        let rights = match self.mask {
+            0x001F_01FF => "F".to_string(),
+            mask if mask & GENERIC_ALL != 0 => "F".to_string(),
+            0x0013_01BF => "M".to_string(),
+            0x0012_00A9 => "RX".to_string(),
+            0x0012_0089 => "R".to_string(),
+            0x0010_0116 => "W".to_string(),
+            mask => format!("0x{mask:x}"),
+        };
+        let flags = [
+            (Self::OBJECT_INHERIT, "(OI)"),
+            (Self::CONTAINER_INHERIT, "(CI)"),
+            (Self::INHERIT_ONLY, "(IO)"),
+            (Self::NO_PROPAGATE, "(NP)"),
+            (Self::INHERITED, "(I)"),
+        ];
+        let flags: String = flags
+            .iter()
+            .filter(|&&(bit, _)| self.flags & bit != 0)
+            .map(|&(_, text)| text)
+            .collect();
+        format!("{flags}({rights})")
+    }
+}
+
+/// The access control list of `path`, following symbolic links, or
+/// `None` where the platform has no way to read one
+pub fn acl<P: AsRef<Path>>(path: P) -> HalResult<Option<Acl>> {
+    let path = path.as_ref();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(path)
+            .map_err(|e| HalError::io_error("acl", Some(&path.display().to_string()), e))?
+            .permissions()
+            .mode();
+        // File systems without ACLs have none beyond the mode bits
+        let read = |name: &str| match get_xattr(path, name, true) {
+            Ok(value) => Ok(value),
+            Err(HalError::Unsupported(_)) => Ok(None),
+            Err(e) => Err(e),
+        };
+        let access = match read("system.posix_acl_access")? {
+            Some(value) => PosixAclEntry::parse_linux(&value)?,
+            None => PosixAclEntry::from_mode(mode),
+        };
+        let default = match read("system.posix_acl_default")? {
+            Some(value) => PosixAclEntry::parse_linux(&value)?,
+            None => Vec::new(),
+        };
+        Ok(Some(Acl::Posix { access, default }))
+    }
+    #[cfg(windows)]
+    {
+        windows_acl::read(path).map(Some)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
+
+#[cfg(windows)]
+pub(crate) mod windows_acl {
+    use super::{Acl, WindowsAce};
+    use crate::error::{HalError, HalResult};
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::Security::{
+        GetAce, GetFileSecurityW, GetSecurityDescriptorDacl, GetSecurityDescriptorOwner,
+        LookupAccountSidW, ACE_HEADER, ACL, DACL_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION,
+        SID_NAME_USE,
+    };
+
+    const ACCESS_ALLOWED_ACE_TYPE: u8 = 0;
+    const ACCESS_DENIED_ACE_TYPE: u8 = 1;
+
+    /// The layout shared by the allow and deny entries: the header, the
+    /// mask, then the SID
+    #[repr(C)]
+    struct AccessAce {
+        header: ACE_HEADER,
+        mask: u32,
+        sid_start: u32,
+    }
+
+    pub(super) fn read(path: &Path) -> HalResult<Acl> {
+        let error = |e| HalError::io_error("acl", Some(&path.display().to_string()), e);
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let info = OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION;
+        let mut needed = 0u32;
+        unsafe {
+            GetFileSecurityW(wide.as_ptr(), info, std::ptr::null_mut(), 0, &mut needed);
+        }
+        if needed == 0 {
+            return Err(error(std::io::Error::last_os_error()));
+        }
+        // u64s keep the descriptor aligned
+        let mut descriptor = vec![0u64; (needed as usize).div_ceil(8)];
+        let descriptor = descriptor.as_mut_ptr().cast();
+        unsafe {
+            if GetFileSecurityW(wide.as_ptr(), info, descriptor, needed, &mut needed) == 0 {
+                return Err(error(std::io::Error::last_os_error()));
+            }
+            let mut owner = std::ptr::null_mut();
+            let mut defaulted = 0;
+            GetSecurityDescriptorOwner(descriptor, &mut owner, &mut defaulted);
+            let owner = (!owner.is_null()).then(|| account_name(owner));
+
+            let mut present = 0;
+            let mut dacl: *mut ACL = std::ptr::null_mut();
+            if GetSecurityDescriptorDacl(descriptor, &mut present, &mut dacl, &mut defaulted) == 0 {
+                return Err(error(std::io::Error::last_os_error()));
+            }
+            // Without a list everyone has full access
+            if present == 0 || dacl.is_null() {
+                let everyone = WindowsAce {
+                    trustee: "Everyone".to_string(),
+                    allow: true,
+                    mask: 0x001F_01FF,
+                    flags: 0,
+                };
+                return Ok(Acl::Windows {
+                    owner,
+                    entries: vec![everyone],
+                });
+            }
+            let mut entries = Vec::new();
+            for index in 0..(*dacl).AceCount as u32 {
+                let mut ace = std::ptr::null_mut();
+                if GetAce(dacl, index, &mut ace) == 0 {
+                    continue;
+                }
+                let ace = &*(ace as *const AccessAce);
+                let allow = match ace.header.AceType {
+                    ACCESS_ALLOWED_ACE_TYPE => true,
+                    ACCESS_DENIED_ACE_TYPE => false,
+                    // Object and callback entries are not shown
+                    _ => continue,
+                };
+                let sid = std::ptr::addr_of!(ace.sid_start) as *mut core::ffi::c_void;
+                entries.push(WindowsAce {
+                    trustee: account_name(sid),
+                    allow,
+                    mask: ace.mask,
+                    flags: ace.header.AceFlags,
+                });
+            }
+            Ok(Acl::Windows { owner, entries })
+        }
+    }
+
+    /// `DOMAIN\name` for a SID, or the SID itself as `S-1-5-…` when no
+    /// account has it
+    unsafe fn account_name(sid: *mut core::ffi::c_void) -> String {
+        let mut name = [0u16; 256];
+        let mut domain = [0u16; 256];
+        let (mut name_len, mut domain_len) = (name.len() as u32, domain.len() as u32);
+        let mut use_type: SID_NAME_USE = 0;
+        let found = LookupAccountSidW(
+            std::ptr::null(),
+            sid,
+            name.as_mut_ptr(),
+            &mut name_len,
+            domain.as_mut_ptr(),
+            &mut domain_len,
+            &mut use_type,
+        ) != 0;
+        if !found {
+            return sid_string(sid as *const u8);
+        }
+        let name = String::from_utf16_lossy(&name[..name_len as usize]);
+        match domain_len {
+            0 => name,
+            len => format!(
+                "{}\\{name}",
+                String::from_utf16_lossy(&domain[..len as usize])
+            ),
+        }
+    }
+
+    /// The string form of a SID: revision, identifier authority and the
+    /// sub-authorities
+    pub(crate) unsafe fn sid_string(sid: *const u8) -> String {
+        let revision = *sid;
+        let count = *sid.add(1) as usize;
+        let authority = (2..8).fold(0u64, |value, i| (value << 8) | *sid.add(i) as u64);
+        let mut text = format!("S-{revision}-{authority}");
+        for i in 0..count {
+            let sub = std::ptr::read_unaligned(sid.add(8 + 4 * i) as *const u32);
+            text.push_str(&format!("-{sub}"));
+        }
+        text
+    }
+}
+
+/// A named data stream of a file beside its contents, on NTFS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataStream {
+    /// The stream's name, without the `:$DATA` type
+    pub name: String,
+    pub size: u64,
+}
+
+/// The alternate data streams of `path`; none where the platform has no
+/// such streams
+pub fn alternate_streams<P: AsRef<Path>>(path: P) -> HalResult<Vec<DataStream>> {
+    let path = path.as_ref();
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Foundation::{
+            GetLastError, ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE,
+        };
+        use windows_sys::Win32::Storage::FileSystem::{
+            FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+            WIN32_FIND_STREAM_DATA,
+        };
+
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+        let handle = unsafe {
+            FindFirstStreamW(
+                wide.as_ptr(),
+                FindStreamInfoStandard,
+                std::ptr::addr_of_mut!(data).cast(),
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            // Directories and files on other file systems may have none
+            if unsafe { GetLastError() } == ERROR_HANDLE_EOF {
+                return Ok(Vec::new());
+            }
+            let err = io::Error::last_os_error();
+            return Err(HalError::io_error(
+                "FindFirstStreamW",
+                Some(&path.display().to_string()),
+                err,
+            ));
+        }
+        let mut streams = Vec::new();
+        loop {
+            let len = data
+                .cStreamName
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(data.cStreamName.len());
+            let full = String::from_utf16_lossy(&data.cStreamName[..len]);
+            // Named like `:name:$DATA`; the contents are the unnamed `::$DATA`
+            let name = full
+                .strip_prefix(':')
+                .and_then(|rest| rest.strip_suffix(":$DATA"))
+                .unwrap_or(&full);
+            if !name.is_empty() {
+                streams.push(DataStream {
+                    name: name.to_string(),
+                    size: data.StreamSize as u64,
+                });
+            }
+            if unsafe { FindNextStreamW(handle, std::ptr::addr_of_mut!(data).cast()) } == 0 {
+                break;
+            }
+        }
+        unsafe { FindClose(handle) };
+        Ok(streams)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Ok(Vec::new())
+    }
+}
+
+/// The space a file takes and where, for disk usage accounting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSpace {
+    /// The length of the contents
+    pub apparent: u64,
+    /// The bytes the file system allocated for it, less than the length
+    /// for sparse and compressed files
+    pub allocated: u64,
+    /// The volume the file is on
+    pub device: u64,
+    /// The file's number on its volume, shared by its hard links; `None`
+    /// where the platform has no such number
+    pub file_id: Option<u64>,
+    /// How many names the file has
+    pub links: u64,
+}
+
+/// The space taken by the file at `path` whose metadata is `metadata`,
+/// without following a final symbolic link. On Unix this is all in the
+/// metadata; on Windows the file is opened for its volume serial, file
+/// index and link count, and the allocation is the compressed size.
+pub fn file_space<P: AsRef<Path>>(path: P, metadata: &fs::Metadata) -> HalResult<FileSpace> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let _ = path;
+        Ok(FileSpace {
+            apparent: metadata.len(),
+            // `st_blocks` counts 512 byte units whatever the block size
+            allocated: metadata.blocks() * 512,
+            device: metadata.dev(),
+            file_id: Some(metadata.ino()),
+            links: metadata.nlink(),
+        })
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Foundation::{
+            CloseHandle, GetLastError, SetLastError, INVALID_HANDLE_VALUE, NO_ERROR,
+        };
+        use windows_sys::Win32::Storage::FileSystem::{
+            CreateFileW, GetCompressedFileSizeW, GetFileInformationByHandle,
+            BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+            FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, INVALID_FILE_SIZE, OPEN_EXISTING,
+        };
+
+        let path = path.as_ref();
+        let failed = |call: &str| {
+            HalError::io_error(
+                call,
+                Some(&path.display().to_string()),
+                io::Error::last_os_error(),
+            )
+        };
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        // Opened with no access rights, enough to query the file
+        let handle = unsafe {
+            CreateFileW(
+                wide.as_ptr(),
+                0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(failed("CreateFileW"));
+        }
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+        let ok = unsafe { GetFileInformationByHandle(handle, &mut info) };
+        unsafe { CloseHandle(handle) };
+        if ok == 0 {
+            return Err(failed("GetFileInformationByHandle"));
+        }
+
+        let allocated = if metadata.is_dir() {
+            0
+        } else {
+            let mut high = 0u32;
+            // A size whose low half is all ones is only an error if one was set
+            unsafe { SetLastError(NO_ERROR) };
+            let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+            if low == INVALID_FILE_SIZE && unsafe { GetLastError() } != NO_ERROR {
+                metadata.len()
+            } else {
+                (u64::from(high) << 32) | u64::from(low)
+            }
+        };
+        Ok(FileSpace {
+            apparent: metadata.len(),
+            allocated,
+            device: u64::from(info.dwVolumeSerialNumber),
+            file_id: Some((u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow)),
+            links: u64::from(info.nNumberOfLinks),
+        })
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Ok(FileSpace {
+            apparent: metadata.len(),
+            allocated: metadata.len(),
+            device: 0,
+            file_id: None,
+            links: 1,
+        })
+    }
+}
+
 /// Check whether a path exists on the filesystem.
 pub fn exists<P: AsRef<Path>>(path: P) -> HalResult<bool> {
     let path = path.as_ref();
@@ -1260,3 +2363,268 @@ mod filesystem_copy_tests {
         assert!(dst_path.exists());
     }
 }
+
+#[cfg(test)]
+mod resolve_tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn resolves_dots_and_missing_components() {
+        let fs = FileSystem::new().expect("Failed to create filesystem");
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs
+            .resolve(temp_dir.path(), ResolveMode::Existing)
+            .expect("Failed to resolve temp dir");
+        std::fs::create_dir(root.join("sub")).unwrap();
+
+        let dotted = root.join("sub").join(".").join("..").join("sub");
+        assert_eq!(
+            fs.resolve(&dotted, ResolveMode::Existing).unwrap(),
+            root.join("sub")
+        );
+
+        let missing = root.join("sub").join("new");
+        assert!(fs.resolve(&missing, ResolveMode::Existing).is_err());
+        assert_eq!(fs.resolve(&missing, ResolveMode::Parents).unwrap(), missing);
+        let deeper = missing.join("deeper");
+        assert!(fs.resolve(&deeper, ResolveMode::Parents).is_err());
+        assert_eq!(fs.resolve(&deeper, ResolveMode::Missing).unwrap(), deeper);
+        assert!(fs.resolve("relative", ResolveMode::Missing).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symbolic_links() {
+        let fs = FileSystem::new().expect("Failed to create filesystem");
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs.resolve(temp_dir.path(), ResolveMode::Existing).unwrap();
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::os::unix::fs::symlink("a/b", root.join("link")).unwrap();
+        std::os::unix::fs::symlink(root.join("a"), root.join("absolute")).unwrap();
+        std::os::unix::fs::symlink("loop", root.join("loop")).unwrap();
+
+        // `..` applies to where the link leads, not to the link itself
+        assert_eq!(
+            fs.resolve(root.join("link/.."), ResolveMode::Existing)
+                .unwrap(),
+            root.join("a")
+        );
+        assert_eq!(
+            fs.resolve(root.join("absolute/b"), ResolveMode::Existing)
+                .unwrap(),
+            root.join("a/b")
+        );
+        assert!(fs.resolve(root.join("loop"), ResolveMode::Missing).is_err());
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Polls until the watcher reports changes, which takes two polls
+    fn settled(watcher: &mut FileWatcher) -> Vec<FileChange> {
+        watcher.wait(Some(Duration::from_secs(5)))
+    }
+
+    #[test]
+    fn reports_settled_changes_once() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("plugin.wasm");
+        let mut watcher = FileWatcher::new(Duration::from_millis(10));
+        watcher.watch(&path);
+        assert!(watcher.poll().is_empty());
+
+        std::fs::write(&path, b"one").unwrap();
+        // The first poll sees the change, the next one reports it
+        assert!(watcher.poll().is_empty());
+        assert_eq!(watcher.poll(), vec![FileChange::Created(path.clone())]);
+        assert!(watcher.poll().is_empty());
+
+        std::fs::write(&path, b"two, longer").unwrap();
+        assert_eq!(
+            settled(&mut watcher),
+            vec![FileChange::Modified(path.clone())]
+        );
+
+        // Replacing the file by a rename is a modification too
+        let staged = temp_dir.path().join("staged");
+        std::fs::write(&staged, b"three, replaced").unwrap();
+        std::fs::rename(&staged, &path).unwrap();
+        assert_eq!(
+            settled(&mut watcher),
+            vec![FileChange::Modified(path.clone())]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            settled(&mut watcher),
+            vec![FileChange::Removed(path.clone())]
+        );
+
+        watcher.unwatch(&path);
+        std::fs::write(&path, b"four").unwrap();
+        assert!(watcher.wait(Some(Duration::from_millis(50))).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod attribute_tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Whether the temporary directory's file system keeps user attributes
+    fn supported(path: &Path) -> bool {
+        !matches!(list_xattrs(path, true), Err(HalError::Unsupported(_)))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn sets_lists_and_removes_attributes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("file");
+        std::fs::write(&path, b"contents").unwrap();
+        if !supported(&path) {
+            return;
+        }
+        assert_eq!(get_xattr(&path, "user.nxsh.test", true).unwrap(), None);
+        set_xattr(&path, "user.nxsh.test", b"one\0two", true).unwrap();
+        set_xattr(&path, "user.nxsh.empty", b"", true).unwrap();
+        let mut names = list_xattrs(&path, true).unwrap();
+        names.sort();
+        assert_eq!(names, vec!["user.nxsh.empty", "user.nxsh.test"]);
+        assert_eq!(
+            get_xattr(&path, "user.nxsh.test", true).unwrap(),
+            Some(b"one\0two".to_vec())
+        );
+        assert_eq!(
+            get_xattr(&path, "user.nxsh.empty", true).unwrap(),
+            Some(Vec::new())
+        );
+
+        remove_xattr(&path, "user.nxsh.test", true).unwrap();
+        assert!(remove_xattr(&path, "user.nxsh.test", true).is_err());
+        assert_eq!(list_xattrs(&path, true).unwrap(), vec!["user.nxsh.empty"]);
+        assert!(list_xattrs(temp_dir.path().join("missing"), true).is_err());
+    }
+
+    #[test]
+    fn decodes_linux_acl_attributes() {
+        let mut value = 2u32.to_le_bytes().to_vec();
+        for (tag, perm, id) in [
+            (0x01u16, 6u16, u32::MAX),
+            (0x02, 4, 1000),
+            (0x04, 4, u32::MAX),
+            (0x10, 5, u32::MAX),
+            (0x20, 0, u32::MAX),
+        ] {
+            value.extend(tag.to_le_bytes());
+            value.extend(perm.to_le_bytes());
+            value.extend(id.to_le_bytes());
+        }
+        let entries = PosixAclEntry::parse_linux(&value).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[1].tag, PosixAclTag::User);
+        assert_eq!(entries[1].id, Some(1000));
+        assert_eq!(entries[0].id, None);
+        let perms: Vec<String> = entries.iter().map(|e| e.permission_string()).collect();
+        assert_eq!(perms, ["rw-", "r--", "r--", "r-x", "---"]);
+        assert!(PosixAclEntry::parse_linux(&value[..7]).is_err());
+        assert!(PosixAclEntry::parse_linux(&[1, 0, 0, 0]).is_err());
+
+        let minimal = PosixAclEntry::from_mode(0o750);
+        let perms: Vec<String> = minimal.iter().map(|e| e.permission_string()).collect();
+        assert_eq!(perms, ["rwx", "r-x", "---"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_acls_and_falls_back_to_the_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("file");
+        std::fs::write(&path, b"contents").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        let acl = acl(&path).unwrap().unwrap();
+        assert_eq!(
+            acl,
+            Acl::Posix {
+                access: PosixAclEntry::from_mode(0o640),
+                default: Vec::new()
+            }
+        );
+        assert!(!acl.is_extended());
+
+        // An entry for another user, written the way setfacl does
+        let mut value = 2u32.to_le_bytes().to_vec();
+        for (tag, perm, id) in [
+            (0x01u16, 6u16, u32::MAX),
+            (0x02, 4, 4242),
+            (0x04, 4, u32::MAX),
+            (0x10, 4, u32::MAX),
+            (0x20, 0, u32::MAX),
+        ] {
+            value.extend(tag.to_le_bytes());
+            value.extend(perm.to_le_bytes());
+            value.extend(id.to_le_bytes());
+        }
+        if set_xattr(&path, "system.posix_acl_access", &value, true).is_err() {
+            // No ACL support, or not allowed to set one
+            return;
+        }
+        let Acl::Posix { access, default } = super::acl(&path).unwrap().unwrap() else {
+            panic!("not a POSIX ACL");
+        };
+        assert_eq!(access.len(), 5);
+        assert_eq!(access[1].id, Some(4242));
+        assert!(default.is_empty());
+    }
+
+    #[test]
+    fn shows_windows_rights_like_icacls() {
+        let ace = |mask, flags| WindowsAce {
+            trustee: "BUILTIN\\Users".to_string(),
+            allow: true,
+            mask,
+            flags,
+        };
+        assert_eq!(ace(0x001F_01FF, 0x03).rights(), "(OI)(CI)(F)");
+        assert_eq!(ace(0x0012_00A9, 0x10).rights(), "(I)(RX)");
+        assert_eq!(ace(0x0000_0001, 0).rights(), "(0x1)");
+        assert!(ace(0x0012_00A9, 0x10).is_inherited());
+    }
+
+    #[test]
+    fn counts_the_space_of_a_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("file");
+        std::fs::write(&path, vec![b'x'; 10_000]).unwrap();
+        let link = temp_dir.path().join("link");
+        std::fs::hard_link(&path, &link).unwrap();
+
+        let space = file_space(&path, &std::fs::symlink_metadata(&path).unwrap()).unwrap();
+        assert_eq!(space.apparent, 10_000);
+        assert_eq!(space.links, 2);
+        let other = file_space(&link, &std::fs::symlink_metadata(&link).unwrap()).unwrap();
+        assert_eq!(other.file_id, space.file_id);
+        assert_eq!(other.device, space.device);
+        if cfg!(unix) {
+            // Whole blocks, unless the file system compresses
+            assert!(space.allocated == 0 || space.allocated >= 10_000);
+        }
+    }
+
+    #[test]
+    fn other_platforms_have_no_streams() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("file");
+        std::fs::write(&path, b"contents").unwrap();
+        let streams = alternate_streams(&path).unwrap();
+        if cfg!(not(windows)) {
+            assert!(streams.is_empty());
+        }
+    }
+}
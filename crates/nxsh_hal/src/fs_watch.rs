@@ -0,0 +1,648 @@
+//! Watching directory trees for changes
+//!
+//! An [`FsWatcher`] has the operating system report changes as they happen:
+//! inotify on Linux, `ReadDirectoryChangesW` on Windows. Other systems,
+//! macOS among them, fall back to scanning the watched trees every
+//! [`POLL_INTERVAL`]. Whatever reports them, [`FsWatcher::wait`] hands the
+//! changes out debounced: once one arrives it keeps collecting until the
+//! trees have been quiet for the debounce interval, and folds the changes
+//! to each file into one, so saving a file or checking out a branch is a
+//! single batch. Only files are reported; a directory that appears is
+//! reported as its files.
+//!
+//! [`FileWatcher`](crate::fs::FileWatcher) polls a few known files instead,
+//! which also works on network mounts that send no change notifications.
+
+use crate::error::HalResult;
+use crate::fs::FileChange;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often trees are scanned where the system reports no changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The longest a batch is held back while changes keep coming, so a file
+/// that is written to without pause still gets reported
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Watches files and directory trees for changes
+#[derive(Debug)]
+pub struct FsWatcher {
+    debounce: Duration,
+    backend: platform::Backend,
+}
+
+impl FsWatcher {
+    /// Create a watcher that reports a batch once changes have stopped for
+    /// `debounce`
+    pub fn new(debounce: Duration) -> HalResult<Self> {
+        Ok(FsWatcher {
+            debounce,
+            backend: platform::Backend::new()?,
+        })
+    }
+
+    /// Start watching `path`: a file, or a directory with everything below
+    /// it, including directories created later
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) -> HalResult<()> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(crate::HalError::io_error(
+                "watch",
+                Some(&path.display().to_string()),
+                std::io::ErrorKind::NotFound.into(),
+            ));
+        }
+        self.backend.watch(path)
+    }
+
+    /// Wait until a batch of changes has settled or `timeout` passes,
+    /// returning the changes, or none on timeout; without a timeout, wait
+    /// for a change
+    pub fn wait(&mut self, timeout: Option<Duration>) -> HalResult<Vec<FileChange>> {
+        let mut changes = self.backend.read(timeout)?;
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+        let first = Instant::now();
+        while first.elapsed() < MAX_DELAY {
+            let more = self.backend.read(Some(self.debounce))?;
+            if more.is_empty() {
+                break;
+            }
+            changes.extend(more);
+        }
+        Ok(coalesce(changes))
+    }
+}
+
+/// Fold the changes to each file into the one that describes them all, in
+/// the order the files first changed
+fn coalesce(changes: Vec<FileChange>) -> Vec<FileChange> {
+    let mut merged: Vec<Option<FileChange>> = Vec::new();
+    let mut index: HashMap<PathBuf, usize> = HashMap::new();
+    for change in changes {
+        let path = change.path().to_path_buf();
+        let Some(&i) = index.get(&path) else {
+            index.insert(path, merged.len());
+            merged.push(Some(change));
+            continue;
+        };
+        merged[i] = match (merged[i].take(), change) {
+            (None, change) => Some(change),
+            // Came and went
+            (Some(FileChange::Created(_)), FileChange::Removed(_)) => None,
+            (Some(FileChange::Created(_)), _) => Some(FileChange::Created(path)),
+            (_, FileChange::Removed(_)) => Some(FileChange::Removed(path)),
+            // Changed, or removed and put back
+            _ => Some(FileChange::Modified(path)),
+        };
+    }
+    merged.into_iter().flatten().collect()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use crate::error::{HalError, HalResult};
+    use crate::fs::FileChange;
+    use nix::errno::Errno;
+    use nix::poll::{poll, PollFd, PollFlags};
+    use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    #[derive(Debug)]
+    pub(super) struct Backend {
+        inotify: Inotify,
+        watches: HashMap<WatchDescriptor, Watch>,
+    }
+
+    /// What one inotify watch on a directory stands for
+    #[derive(Debug)]
+    struct Watch {
+        dir: PathBuf,
+        /// Whether directories created in it are watched as well
+        recursive: bool,
+        /// The files of interest when only single files are watched
+        only: Option<HashSet<OsString>>,
+    }
+
+    fn failed(operation: &str, errno: Errno) -> HalError {
+        HalError::io_error(operation, None, std::io::Error::from(errno))
+    }
+
+    fn mask() -> AddWatchFlags {
+        AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+    }
+
+    impl Backend {
+        pub(super) fn new() -> HalResult<Self> {
+            let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+                .map_err(|e| failed("inotify_init", e))?;
+            Ok(Backend {
+                inotify,
+                watches: HashMap::new(),
+            })
+        }
+
+        pub(super) fn watch(&mut self, path: &Path) -> HalResult<()> {
+            if path.is_dir() {
+                return self.add_tree(path, None);
+            }
+            // A file is watched through its directory, so it is still seen
+            // when an editor replaces it by renaming another file over it
+            let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(HalError::invalid(&format!(
+                    "cannot watch {}",
+                    path.display()
+                )));
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            self.add(dir, false, Some(name.to_os_string()))
+        }
+
+        fn add(&mut self, dir: &Path, recursive: bool, only: Option<OsString>) -> HalResult<()> {
+            let wd = self
+                .inotify
+                .add_watch(dir, mask())
+                .map_err(|e| failed("inotify_add_watch", e))?;
+            // Watching a directory twice gives the same descriptor back
+            let watch = self.watches.entry(wd).or_insert_with(|| Watch {
+                dir: dir.to_path_buf(),
+                recursive,
+                only: Some(HashSet::new()),
+            });
+            watch.recursive |= recursive;
+            match only {
+                Some(name) => {
+                    if let Some(names) = &mut watch.only {
+                        names.insert(name);
+                    }
+                }
+                None => watch.only = None,
+            }
+            Ok(())
+        }
+
+        /// Watch `dir` and the directories below it, adding the files found
+        /// to `created` when it is given
+        fn add_tree(
+            &mut self,
+            dir: &Path,
+            mut created: Option<&mut Vec<FileChange>>,
+        ) -> HalResult<()> {
+            self.add(dir, true, None)?;
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                return Ok(());
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                match entry.file_type() {
+                    Ok(kind) if kind.is_dir() => {
+                        // One that has gone again already is no error
+                        let _ = self.add_tree(&path, created.as_deref_mut());
+                    }
+                    _ => {
+                        if let Some(created) = created.as_deref_mut() {
+                            created.push(FileChange::Created(path));
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        pub(super) fn read(&mut self, timeout: Option<Duration>) -> HalResult<Vec<FileChange>> {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            loop {
+                let left = match deadline {
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        left.as_millis().min(i32::MAX as u128) as i32
+                    }
+                    None => -1,
+                };
+                let mut fds = [PollFd::new(&self.inotify, PollFlags::POLLIN)];
+                match poll(&mut fds, left) {
+                    Ok(0) => return Ok(Vec::new()),
+                    Ok(_) => {}
+                    Err(Errno::EINTR) => continue,
+                    Err(e) => return Err(failed("poll", e)),
+                }
+                let events = match self.inotify.read_events() {
+                    Ok(events) => events,
+                    Err(Errno::EAGAIN | Errno::EINTR) => continue,
+                    Err(e) => return Err(failed("read", e)),
+                };
+                let mut changes = Vec::new();
+                for event in events {
+                    if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                        // The directory is gone
+                        self.watches.remove(&event.wd);
+                        continue;
+                    }
+                    let (Some(watch), Some(name)) = (self.watches.get(&event.wd), event.name)
+                    else {
+                        continue;
+                    };
+                    if watch
+                        .only
+                        .as_ref()
+                        .is_some_and(|only| !only.contains(&name))
+                    {
+                        continue;
+                    }
+                    let path = watch.dir.join(name);
+                    let appeared = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO;
+                    let went = AddWatchFlags::IN_DELETE | AddWatchFlags::IN_MOVED_FROM;
+                    if event.mask.contains(AddWatchFlags::IN_ISDIR) {
+                        if watch.recursive && event.mask.intersects(appeared) {
+                            let _ = self.add_tree(&path, Some(&mut changes));
+                        }
+                        continue;
+                    }
+                    changes.push(if event.mask.intersects(appeared) {
+                        FileChange::Created(path)
+                    } else if event.mask.intersects(went) {
+                        FileChange::Removed(path)
+                    } else {
+                        FileChange::Modified(path)
+                    });
+                }
+                if !changes.is_empty() {
+                    return Ok(changes);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use crate::error::{HalError, HalResult};
+    use crate::fs::FileChange;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, ReadDirectoryChangesW, FILE_ACTION_ADDED, FILE_ACTION_REMOVED,
+        FILE_ACTION_RENAMED_NEW_NAME, FILE_ACTION_RENAMED_OLD_NAME, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE_DIR_NAME, FILE_NOTIFY_CHANGE_FILE_NAME,
+        FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE, FILE_NOTIFY_INFORMATION,
+        FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::IO::CancelIoEx;
+
+    #[derive(Debug)]
+    pub(super) struct Backend {
+        sender: Sender<FileChange>,
+        receiver: Receiver<FileChange>,
+        dirs: Vec<DirWatch>,
+    }
+
+    /// A thread waiting for changes in one directory
+    #[derive(Debug)]
+    struct DirWatch {
+        handle: HANDLE,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Backend {
+        pub(super) fn new() -> HalResult<Self> {
+            let (sender, receiver) = mpsc::channel();
+            Ok(Backend {
+                sender,
+                receiver,
+                dirs: Vec::new(),
+            })
+        }
+
+        pub(super) fn watch(&mut self, path: &Path) -> HalResult<()> {
+            let (dir, only) = if path.is_dir() {
+                (path, None)
+            } else {
+                match (path.parent(), path.file_name()) {
+                    (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => {
+                        (dir, Some(name.to_os_string()))
+                    }
+                    (_, Some(name)) => (Path::new("."), Some(name.to_os_string())),
+                    _ => {
+                        return Err(HalError::invalid(&format!(
+                            "cannot watch {}",
+                            path.display()
+                        )))
+                    }
+                }
+            };
+            let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+            // SAFETY: the name is NUL-terminated; backup semantics let a
+            // directory be opened
+            let handle = unsafe {
+                CreateFileW(
+                    wide.as_ptr(),
+                    FILE_LIST_DIRECTORY,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                    std::ptr::null(),
+                    OPEN_EXISTING,
+                    FILE_FLAG_BACKUP_SEMANTICS,
+                    0,
+                )
+            };
+            if handle == INVALID_HANDLE_VALUE {
+                return Err(HalError::io_error(
+                    "CreateFileW",
+                    Some(&dir.display().to_string()),
+                    std::io::Error::last_os_error(),
+                ));
+            }
+            let sender = self.sender.clone();
+            let dir = dir.to_path_buf();
+            let recursive = only.is_none();
+            let thread = std::thread::spawn(move || {
+                read_changes(handle, &dir, recursive, only.as_ref(), &sender)
+            });
+            self.dirs.push(DirWatch {
+                handle,
+                thread: Some(thread),
+            });
+            Ok(())
+        }
+
+        pub(super) fn read(&mut self, timeout: Option<Duration>) -> HalResult<Vec<FileChange>> {
+            let first = match timeout {
+                Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                    Ok(change) => change,
+                    Err(RecvTimeoutError::Timeout) => return Ok(Vec::new()),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        unreachable!("the backend holds a sender")
+                    }
+                },
+                None => self.receiver.recv().expect("the backend holds a sender"),
+            };
+            Ok(std::iter::once(first)
+                .chain(self.receiver.try_iter())
+                .collect())
+        }
+    }
+
+    /// Send the changes in `dir` until the handle is cancelled
+    fn read_changes(
+        handle: HANDLE,
+        dir: &Path,
+        recursive: bool,
+        only: Option<&OsString>,
+        sender: &Sender<FileChange>,
+    ) {
+        // DWORD-aligned, as the call requires
+        let mut buffer = vec![0u32; 16 * 1024];
+        loop {
+            let mut returned = 0;
+            // SAFETY: the buffer outlives the synchronous call
+            let ok = unsafe {
+                ReadDirectoryChangesW(
+                    handle,
+                    buffer.as_mut_ptr() as *mut std::ffi::c_void,
+                    (buffer.len() * 4) as u32,
+                    recursive as i32,
+                    FILE_NOTIFY_CHANGE_FILE_NAME
+                        | FILE_NOTIFY_CHANGE_DIR_NAME
+                        | FILE_NOTIFY_CHANGE_LAST_WRITE
+                        | FILE_NOTIFY_CHANGE_SIZE,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                    None,
+                )
+            };
+            if ok == 0 {
+                // Cancelled, or the directory went away
+                return;
+            }
+            if returned == 0 {
+                // Too many changes to list: report the directory instead
+                if sender
+                    .send(FileChange::Modified(dir.to_path_buf()))
+                    .is_err()
+                {
+                    return;
+                }
+                continue;
+            }
+            let mut offset = 0usize;
+            loop {
+                // SAFETY: the system wrote a chain of records into the buffer,
+                // each starting at a DWORD-aligned offset within it
+                let (next, action, name) = unsafe {
+                    let record = (buffer.as_ptr() as *const u8).add(offset)
+                        as *const FILE_NOTIFY_INFORMATION;
+                    let length = (*record).FileNameLength as usize / 2;
+                    let name = std::slice::from_raw_parts((*record).FileName.as_ptr(), length);
+                    (
+                        (*record).NextEntryOffset as usize,
+                        (*record).Action,
+                        OsString::from_wide(name),
+                    )
+                };
+                let wanted = only.map_or(true, |only| *only == name);
+                let path: PathBuf = dir.join(&name);
+                let change = match action {
+                    FILE_ACTION_ADDED | FILE_ACTION_RENAMED_NEW_NAME => {
+                        Some(FileChange::Created(path))
+                    }
+                    FILE_ACTION_REMOVED | FILE_ACTION_RENAMED_OLD_NAME => {
+                        Some(FileChange::Removed(path))
+                    }
+                    _ => Some(FileChange::Modified(path)),
+                };
+                // Directories are reported as the files in them
+                if let Some(change) = change.filter(|c| wanted && !c.path().is_dir()) {
+                    if sender.send(change).is_err() {
+                        return;
+                    }
+                }
+                if next == 0 {
+                    break;
+                }
+                offset += next;
+            }
+        }
+    }
+
+    impl Drop for DirWatch {
+        fn drop(&mut self) {
+            // SAFETY: the handle is owned by `self`; cancelling ends the
+            // thread's pending read, and the handle is closed once it is done
+            unsafe { CancelIoEx(self.handle, std::ptr::null()) };
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+            // SAFETY: no read uses the handle any more
+            unsafe { CloseHandle(self.handle) };
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+mod platform {
+    use super::POLL_INTERVAL;
+    use crate::error::HalResult;
+    use crate::fs::{FileChange, FileStamp};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    /// Scans the watched trees and compares them with the last scan
+    #[derive(Debug)]
+    pub(super) struct Backend {
+        roots: Vec<PathBuf>,
+        files: HashMap<PathBuf, FileStamp>,
+    }
+
+    impl Backend {
+        pub(super) fn new() -> HalResult<Self> {
+            Ok(Backend {
+                roots: Vec::new(),
+                files: HashMap::new(),
+            })
+        }
+
+        pub(super) fn watch(&mut self, path: &Path) -> HalResult<()> {
+            scan(path, &mut self.files);
+            self.roots.push(path.to_path_buf());
+            Ok(())
+        }
+
+        pub(super) fn read(&mut self, timeout: Option<Duration>) -> HalResult<Vec<FileChange>> {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            loop {
+                let mut files = HashMap::new();
+                for root in &self.roots {
+                    scan(root, &mut files);
+                }
+                let mut changes: Vec<FileChange> = files
+                    .iter()
+                    .filter_map(|(path, stamp)| match self.files.get(path) {
+                        None => Some(FileChange::Created(path.clone())),
+                        Some(old) if old != stamp => Some(FileChange::Modified(path.clone())),
+                        Some(_) => None,
+                    })
+                    .collect();
+                changes.extend(
+                    self.files
+                        .keys()
+                        .filter(|path| !files.contains_key(*path))
+                        .map(|path| FileChange::Removed(path.clone())),
+                );
+                self.files = files;
+                if !changes.is_empty() {
+                    return Ok(changes);
+                }
+                let pause = match deadline {
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        if left.is_zero() {
+                            return Ok(changes);
+                        }
+                        left.min(POLL_INTERVAL)
+                    }
+                    None => POLL_INTERVAL,
+                };
+                std::thread::sleep(pause);
+            }
+        }
+    }
+
+    /// Record the files at or below `path`, without following links to
+    /// directories
+    fn scan(path: &Path, files: &mut HashMap<PathBuf, FileStamp>) {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            if let Some(stamp) = FileStamp::of(path) {
+                files.insert(path.to_path_buf(), stamp);
+            }
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => scan(&path, files),
+                _ => {
+                    if let Some(stamp) = FileStamp::of(&path) {
+                        files.insert(path, stamp);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn coalesces_the_changes_to_each_file() {
+        let (a, b, c) = (PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c"));
+        let changes = vec![
+            FileChange::Created(a.clone()),
+            FileChange::Modified(b.clone()),
+            FileChange::Modified(a.clone()),
+            FileChange::Created(c.clone()),
+            FileChange::Removed(b.clone()),
+            FileChange::Removed(c.clone()),
+        ];
+        assert_eq!(
+            coalesce(changes),
+            vec![FileChange::Created(a), FileChange::Removed(b)]
+        );
+        let replaced = vec![
+            FileChange::Removed(PathBuf::from("d")),
+            FileChange::Created(PathBuf::from("d")),
+        ];
+        assert_eq!(
+            coalesce(replaced),
+            vec![FileChange::Modified(PathBuf::from("d"))]
+        );
+    }
+
+    #[test]
+    fn reports_a_batch_for_a_tree() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path().to_path_buf();
+        let mut watcher = FsWatcher::new(Duration::from_millis(50)).unwrap();
+        watcher.watch(&root).unwrap();
+        assert!(watcher
+            .wait(Some(Duration::from_millis(50)))
+            .unwrap()
+            .is_empty());
+
+        // Files in new directories are reported as they are
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), b"one").unwrap();
+        std::fs::write(root.join("src/lib.rs"), b"two").unwrap();
+        let changes = watcher.wait(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(changes, vec![FileChange::Created(root.join("src/lib.rs"))]);
+
+        std::fs::remove_file(root.join("src/lib.rs")).unwrap();
+        let changes = watcher.wait(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(changes, vec![FileChange::Removed(root.join("src/lib.rs"))]);
+
+        assert!(watcher.watch(root.join("missing")).is_err());
+    }
+}
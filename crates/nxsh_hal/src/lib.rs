@@ -12,19 +12,31 @@
 
 pub mod command;
 pub mod completion;
+pub mod copy;
 pub mod error;
 pub mod fast_completion;
 pub mod fs;
 pub mod fs_enhanced;
+pub mod fs_watch;
+pub mod magic;
 pub mod memory;
+pub mod mounts;
 pub mod network;
 pub mod pipe;
 pub mod platform;
+pub mod privilege;
 pub mod process;
 pub mod process_enhanced;
+pub mod pty;
+pub mod resource;
+pub mod sandbox;
 pub mod seccomp;
+pub mod signal;
+pub mod socket;
+pub mod terminal;
 pub mod time;
 pub mod time_enhanced;
+pub mod trash;
 
 pub use error::{HalError, HalResult};
 
@@ -33,12 +45,25 @@ pub use platform::{detect_platform, Capabilities, Platform};
 
 pub use command::{Command, CommandResult};
 /// Re-export commonly used types
-pub use fs::{DirectoryHandle, FileHandle, FileMetadata, FileSystem};
+pub use fs::{
+    DirectoryHandle, FileChange, FileHandle, FileMetadata, FileSpace, FileSystem, FileWatcher,
+    ResolveMode,
+};
+pub use fs_watch::FsWatcher;
 pub use memory::{MemoryInfo, MemoryManager};
+pub use mounts::{BlockDevice, BlockDeviceKind, FsUsage, MountEntry};
 pub use network::NetworkManager;
 pub use pipe::{PipeHandle, PipeManager};
-pub use process::{ProcessHandle, ProcessInfo, ProcessManager};
+pub use process::{
+    ChildState, ProcessEntry, ProcessHandle, ProcessInfo, ProcessManager, ProcessSnapshot,
+    ProcessStatus, Scheduling, TerminalControl,
+};
+pub use pty::{Pty, PtyChild};
+pub use resource::{Resource, ResourceLimit};
+pub use signal::ShellSignal;
+pub use socket::{SocketEntry, SocketProtocol, SocketState};
 pub use time::TimeManager;
+pub use trash::TrashedItem;
 
 /// Initialize the HAL with platform-specific optimizations
 pub fn initialize() -> HalResult<()> {
@@ -0,0 +1,621 @@
+//! File type detection from magic numbers
+//!
+//! Identifies a file from its first few kilobytes rather than its name:
+//! executables (ELF, PE, Mach-O, WebAssembly), archives and compressed
+//! streams, images, audio and video containers, common documents, scripts
+//! by their `#!` line and text by its encoding. Each result carries a
+//! description worded like `file(1)`, a MIME type and a broad class that
+//! callers such as completion and `ls` can turn into a label or an icon.
+
+use crate::error::{HalError, HalResult};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// How many leading bytes are examined
+pub const SNIFF_LEN: usize = 4096;
+
+/// Broad kind of content a file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileClass {
+    Empty,
+    Text,
+    Script,
+    Executable,
+    Archive,
+    Image,
+    Audio,
+    Video,
+    Document,
+    Data,
+}
+
+impl FileClass {
+    /// A short name for the class, used as a completion description
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileClass::Empty => "Empty file",
+            FileClass::Text => "Text",
+            FileClass::Script => "Script",
+            FileClass::Executable => "Executable",
+            FileClass::Archive => "Archive",
+            FileClass::Image => "Image",
+            FileClass::Audio => "Audio",
+            FileClass::Video => "Video",
+            FileClass::Document => "Document",
+            FileClass::Data => "Data",
+        }
+    }
+}
+
+/// What the leading bytes of a file say it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Magic {
+    /// Human readable description, e.g. `ELF 64-bit LSB executable, x86-64`
+    pub description: String,
+    /// MIME type, e.g. `application/x-executable`
+    pub mime: &'static str,
+    /// Character set for `--mime` output: a text encoding or `binary`
+    pub charset: &'static str,
+    pub class: FileClass,
+}
+
+impl Magic {
+    fn new(description: impl Into<String>, mime: &'static str, class: FileClass) -> Self {
+        Magic {
+            description: description.into(),
+            mime,
+            charset: "binary",
+            class,
+        }
+    }
+}
+
+/// Identify the file at PATH from its first `SNIFF_LEN` bytes
+pub fn identify_path<P: AsRef<Path>>(path: P) -> HalResult<Magic> {
+    let path = path.as_ref();
+    let io_error =
+        |e| HalError::io_error("identify", Some(path.to_str().unwrap_or("<invalid>")), e);
+    let file = File::open(path).map_err(io_error)?;
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .map_err(io_error)?;
+    Ok(identify(&head))
+}
+
+/// Identify content from its leading bytes
+pub fn identify(bytes: &[u8]) -> Magic {
+    if bytes.is_empty() {
+        return Magic::new("empty", "inode/x-empty", FileClass::Empty);
+    }
+    executable(bytes)
+        .or_else(|| archive(bytes))
+        .or_else(|| media(bytes))
+        .or_else(|| document(bytes))
+        .or_else(|| text(bytes))
+        .unwrap_or_else(|| Magic::new("data", "application/octet-stream", FileClass::Data))
+}
+
+fn u16_at(bytes: &[u8], at: usize, big_endian: bool) -> Option<u16> {
+    let raw: [u8; 2] = bytes.get(at..at + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(raw)
+    } else {
+        u16::from_le_bytes(raw)
+    })
+}
+
+fn u32_at(bytes: &[u8], at: usize, big_endian: bool) -> Option<u32> {
+    let raw: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(raw)
+    } else {
+        u32::from_le_bytes(raw)
+    })
+}
+
+fn executable(bytes: &[u8]) -> Option<Magic> {
+    if bytes.starts_with(b"\x7fELF") {
+        return Some(elf(bytes));
+    }
+    if bytes.starts_with(b"MZ") {
+        return Some(pe(bytes));
+    }
+    if bytes.starts_with(b"\0asm") {
+        return Some(Magic::new(
+            "WebAssembly (wasm) binary module",
+            "application/wasm",
+            FileClass::Executable,
+        ));
+    }
+    let mach_o = |bits| {
+        Magic::new(
+            format!("Mach-O {bits}-bit executable"),
+            "application/x-mach-binary",
+            FileClass::Executable,
+        )
+    };
+    match bytes.get(..4)? {
+        [0xfe, 0xed, 0xfa, 0xce] | [0xce, 0xfa, 0xed, 0xfe] => Some(mach_o(32)),
+        [0xfe, 0xed, 0xfa, 0xcf] | [0xcf, 0xfa, 0xed, 0xfe] => Some(mach_o(64)),
+        // Universal binaries and Java classes share a magic number; a
+        // universal binary's next word is a small architecture count
+        [0xca, 0xfe, 0xba, 0xbe] => Some(match u32_at(bytes, 4, true)? {
+            count if count < 20 => Magic::new(
+                format!("Mach-O universal binary with {count} architectures"),
+                "application/x-mach-binary",
+                FileClass::Executable,
+            ),
+            _ => Magic::new(
+                "compiled Java class data",
+                "application/x-java-applet",
+                FileClass::Executable,
+            ),
+        }),
+        _ => None,
+    }
+}
+
+fn elf(bytes: &[u8]) -> Magic {
+    let bits = match bytes.get(4) {
+        Some(2) => "64-bit",
+        _ => "32-bit",
+    };
+    let big_endian = bytes.get(5) == Some(&2);
+    let order = if big_endian { "MSB" } else { "LSB" };
+    let (kind, mime) = match u16_at(bytes, 16, big_endian) {
+        Some(1) => ("relocatable", "application/x-object"),
+        Some(2) => ("executable", "application/x-executable"),
+        Some(3) => ("shared object", "application/x-sharedlib"),
+        Some(4) => ("core file", "application/x-coredump"),
+        _ => ("file", "application/octet-stream"),
+    };
+    let machine = match u16_at(bytes, 18, big_endian) {
+        Some(0x03) => ", Intel 80386",
+        Some(0x08) => ", MIPS",
+        Some(0x14) => ", PowerPC",
+        Some(0x15) => ", 64-bit PowerPC",
+        Some(0x28) => ", ARM",
+        Some(0x3e) => ", x86-64",
+        Some(0xb7) => ", ARM aarch64",
+        Some(0xf3) => ", UCB RISC-V",
+        _ => "",
+    };
+    Magic::new(
+        format!("ELF {bits} {order} {kind}{machine}"),
+        mime,
+        FileClass::Executable,
+    )
+}
+
+fn pe(bytes: &[u8]) -> Magic {
+    let dos = || {
+        Magic::new(
+            "MS-DOS executable",
+            "application/x-dosexec",
+            FileClass::Executable,
+        )
+    };
+    // Only headers within the sniffed bytes can be read
+    let header = u32_at(bytes, 0x3c, false).map(|at| at as usize);
+    let Some(header) = header.filter(|&at| at < SNIFF_LEN) else {
+        return dos();
+    };
+    if bytes.get(header..header + 4) != Some(&b"PE\0\0"[..]) {
+        return dos();
+    }
+    let format = match u16_at(bytes, header + 24, false) {
+        Some(0x20b) => "PE32+",
+        _ => "PE32",
+    };
+    let characteristics = u16_at(bytes, header + 22, false).unwrap_or(0);
+    let kind = if characteristics & 0x2000 != 0 {
+        "executable (DLL)"
+    } else {
+        "executable"
+    };
+    let subsystem = match u16_at(bytes, header + 92, false) {
+        Some(2) => " (GUI)",
+        Some(3) => " (console)",
+        _ => "",
+    };
+    let machine = match u16_at(bytes, header + 4, false) {
+        Some(0x14c) => " Intel 80386",
+        Some(0x8664) => " x86-64",
+        Some(0xaa64) => " Aarch64",
+        Some(0x1c4) => " ARMv7 Thumb",
+        _ => "",
+    };
+    Magic::new(
+        format!("{format} {kind}{subsystem}{machine}, for MS Windows"),
+        "application/vnd.microsoft.portable-executable",
+        FileClass::Executable,
+    )
+}
+
+fn archive(bytes: &[u8]) -> Option<Magic> {
+    const SIGNATURES: &[(&[u8], &str, &str)] = &[
+        (b"PK\x03\x04", "Zip archive data", "application/zip"),
+        (b"PK\x05\x06", "Zip archive data (empty)", "application/zip"),
+        (b"\x1f\x8b", "gzip compressed data", "application/gzip"),
+        (b"BZh", "bzip2 compressed data", "application/x-bzip2"),
+        (b"\xfd7zXZ\0", "XZ compressed data", "application/x-xz"),
+        (
+            b"\x28\xb5\x2f\xfd",
+            "Zstandard compressed data",
+            "application/zstd",
+        ),
+        (
+            b"7z\xbc\xaf\x27\x1c",
+            "7-zip archive data",
+            "application/x-7z-compressed",
+        ),
+        (b"Rar!\x1a\x07", "RAR archive data", "application/vnd.rar"),
+        (b"!<arch>\n", "current ar archive", "application/x-archive"),
+        (
+            b"070701",
+            "ASCII cpio archive (SVR4 with no CRC)",
+            "application/x-cpio",
+        ),
+        (
+            b"070707",
+            "ASCII cpio archive (pre-SVR4 or odc)",
+            "application/x-cpio",
+        ),
+    ];
+    for &(signature, description, mime) in SIGNATURES {
+        if bytes.starts_with(signature) {
+            return Some(Magic::new(description, mime, FileClass::Archive));
+        }
+    }
+    match bytes.get(257..262) {
+        Some(b"ustar") => Some(Magic::new(
+            "POSIX tar archive",
+            "application/x-tar",
+            FileClass::Archive,
+        )),
+        _ => None,
+    }
+}
+
+fn media(bytes: &[u8]) -> Option<Magic> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let size = match (u32_at(bytes, 16, true), u32_at(bytes, 20, true)) {
+            (Some(width), Some(height)) => format!(", {width} x {height}"),
+            _ => String::new(),
+        };
+        return Some(Magic::new(
+            format!("PNG image data{size}"),
+            "image/png",
+            FileClass::Image,
+        ));
+    }
+    if bytes.starts_with(b"\xff\xd8\xff") {
+        return Some(Magic::new(
+            "JPEG image data",
+            "image/jpeg",
+            FileClass::Image,
+        ));
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        let version = String::from_utf8_lossy(&bytes[3..6]);
+        let size = match (u16_at(bytes, 6, false), u16_at(bytes, 8, false)) {
+            (Some(width), Some(height)) => format!(", {width} x {height}"),
+            _ => String::new(),
+        };
+        return Some(Magic::new(
+            format!("GIF image data, version {version}{size}"),
+            "image/gif",
+            FileClass::Image,
+        ));
+    }
+    if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        let order = if bytes[0] == b'I' { "little" } else { "big" };
+        return Some(Magic::new(
+            format!("TIFF image data, {order}-endian"),
+            "image/tiff",
+            FileClass::Image,
+        ));
+    }
+    if bytes.starts_with(b"\0\0\x01\0") && bytes.len() >= 22 {
+        return Some(Magic::new(
+            "MS Windows icon resource",
+            "image/vnd.microsoft.icon",
+            FileClass::Image,
+        ));
+    }
+    // "BM" alone is too common, so also check the header size field
+    if bytes.starts_with(b"BM") && matches!(u32_at(bytes, 14, false), Some(12 | 40 | 108 | 124)) {
+        let size = match (u32_at(bytes, 18, false), u32_at(bytes, 22, false)) {
+            (Some(width), Some(height)) => format!(", {width} x {}", height as i32),
+            _ => String::new(),
+        };
+        return Some(Magic::new(
+            format!("PC bitmap{size}"),
+            "image/bmp",
+            FileClass::Image,
+        ));
+    }
+    if bytes.starts_with(b"RIFF") {
+        let riff = |kind: &str, mime, class| {
+            Some(Magic::new(
+                format!("RIFF (little-endian) data, {kind}"),
+                mime,
+                class,
+            ))
+        };
+        return match bytes.get(8..12)? {
+            b"WEBP" => riff("Web/P image", "image/webp", FileClass::Image),
+            b"WAVE" => riff("WAVE audio", "audio/x-wav", FileClass::Audio),
+            b"AVI " => riff("AVI", "video/x-msvideo", FileClass::Video),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(b"ID3") {
+        let version = bytes.get(3).copied().unwrap_or(0);
+        return Some(Magic::new(
+            format!("Audio file with ID3 version 2.{version}"),
+            "audio/mpeg",
+            FileClass::Audio,
+        ));
+    }
+    if bytes.len() >= 2 && bytes[0] == 0xff && bytes[1] & 0xe6 == 0xe2 {
+        return Some(Magic::new(
+            "MPEG ADTS, layer III",
+            "audio/mpeg",
+            FileClass::Audio,
+        ));
+    }
+    if bytes.starts_with(b"fLaC") {
+        return Some(Magic::new(
+            "FLAC audio bitstream data",
+            "audio/flac",
+            FileClass::Audio,
+        ));
+    }
+    if bytes.starts_with(b"OggS") {
+        return Some(Magic::new("Ogg data", "audio/ogg", FileClass::Audio));
+    }
+    if bytes.starts_with(b"\x1a\x45\xdf\xa3") {
+        let webm = bytes.windows(4).take(64).any(|w| w == b"webm");
+        return Some(if webm {
+            Magic::new("WebM", "video/webm", FileClass::Video)
+        } else {
+            Magic::new("Matroska data", "video/x-matroska", FileClass::Video)
+        });
+    }
+    if bytes.get(4..8) == Some(&b"ftyp"[..]) {
+        return Some(match bytes.get(8..12)? {
+            b"M4A " => Magic::new(
+                "ISO Media, Apple iTunes AAC-LC (.M4A) Audio",
+                "audio/mp4",
+                FileClass::Audio,
+            ),
+            b"qt  " => Magic::new(
+                "ISO Media, Apple QuickTime movie",
+                "video/quicktime",
+                FileClass::Video,
+            ),
+            b"heic" | b"heix" | b"mif1" => {
+                Magic::new("ISO Media, HEIF Image", "image/heic", FileClass::Image)
+            }
+            _ => Magic::new("ISO Media", "video/mp4", FileClass::Video),
+        });
+    }
+    None
+}
+
+fn document(bytes: &[u8]) -> Option<Magic> {
+    if let Some(rest) = bytes.strip_prefix(b"%PDF-") {
+        let version: String = rest
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .map(|&b| b as char)
+            .collect();
+        return Some(Magic::new(
+            format!("PDF document, version {version}"),
+            "application/pdf",
+            FileClass::Document,
+        ));
+    }
+    if bytes.starts_with(b"SQLite format 3\0") {
+        return Some(Magic::new(
+            "SQLite 3.x database",
+            "application/vnd.sqlite3",
+            FileClass::Data,
+        ));
+    }
+    if bytes.starts_with(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1") {
+        return Some(Magic::new(
+            "Composite Document File V2 Document",
+            "application/x-ole-storage",
+            FileClass::Document,
+        ));
+    }
+    None
+}
+
+/// The encoding of text content, if it is text
+fn encoding(bytes: &[u8]) -> Option<(&'static str, &'static str, &[u8])> {
+    if let Some(rest) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        return Some(("Unicode text, UTF-8 (with BOM) text", "utf-8", rest));
+    }
+    if bytes.starts_with(b"\xff\xfe\0\0") {
+        return Some(("Unicode text, UTF-32, little-endian text", "utf-32le", &[]));
+    }
+    if bytes.starts_with(b"\xff\xfe") {
+        return Some(("Unicode text, UTF-16, little-endian text", "utf-16le", &[]));
+    }
+    if bytes.starts_with(b"\xfe\xff") {
+        return Some(("Unicode text, UTF-16, big-endian text", "utf-16be", &[]));
+    }
+    // Control characters other than layout ones and escapes mean binary
+    let printable = |b: &u8| !matches!(b, 0..=6 | 0x0e..=0x1a | 0x1c..=0x1f | 0x7f);
+    if !bytes.iter().all(printable) {
+        return None;
+    }
+    if bytes.is_ascii() {
+        return Some(("ASCII text", "us-ascii", bytes));
+    }
+    match std::str::from_utf8(bytes) {
+        // A character cut off at the end of the sniffed bytes is fine
+        Ok(_) => Some(("Unicode text, UTF-8 text", "utf-8", bytes)),
+        Err(e) if e.error_len().is_none() => Some(("Unicode text, UTF-8 text", "utf-8", bytes)),
+        // Latin-1 text avoids the C1 control range
+        Err(_) if !bytes.iter().any(|b| (0x80..0xa0).contains(b)) => {
+            Some(("ISO-8859 text", "iso-8859-1", bytes))
+        }
+        Err(_) => None,
+    }
+}
+
+fn text(bytes: &[u8]) -> Option<Magic> {
+    let (encoding, charset, body) = encoding(bytes)?;
+    let crlf = if body.windows(2).any(|w| w == b"\r\n") {
+        ", with CRLF line terminators"
+    } else {
+        ""
+    };
+    let (kind, mime, class) = if let Some(line) = body.strip_prefix(b"#!") {
+        let (kind, mime) = script(line);
+        (kind, mime, FileClass::Script)
+    } else {
+        let start = body
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(body.len());
+        let lead =
+            String::from_utf8_lossy(&body[start..body.len().min(start + 64)]).to_ascii_lowercase();
+        if lead.starts_with("<?xml") {
+            (
+                "XML 1.0 document".to_string(),
+                "text/xml",
+                FileClass::Document,
+            )
+        } else if lead.starts_with("<!doctype html") || lead.starts_with("<html") {
+            (
+                "HTML document".to_string(),
+                "text/html",
+                FileClass::Document,
+            )
+        } else if lead.starts_with("{\\rtf") {
+            (
+                "Rich Text Format data".to_string(),
+                "text/rtf",
+                FileClass::Document,
+            )
+        } else {
+            let description = format!("{encoding}{crlf}");
+            return Some(Magic {
+                charset,
+                ..Magic::new(description, "text/plain", FileClass::Text)
+            });
+        }
+    };
+    let executable = if class == FileClass::Script {
+        " executable"
+    } else {
+        ""
+    };
+    Some(Magic {
+        charset,
+        ..Magic::new(format!("{kind}, {encoding}{executable}{crlf}"), mime, class)
+    })
+}
+
+/// Describe a script from the rest of its `#!` line
+fn script(line: &[u8]) -> (String, &'static str) {
+    let end = line.iter().position(|&b| b == b'\n').unwrap_or(line.len());
+    let line = String::from_utf8_lossy(&line[..end]);
+    let mut words = line.split_whitespace();
+    let mut program = words.next().unwrap_or("");
+    program = program.rsplit('/').next().unwrap_or(program);
+    // `#!/usr/bin/env [-S] prog` names the interpreter afterwards
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-')).unwrap_or("");
+    }
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match name {
+        "sh" | "dash" => ("POSIX shell script".to_string(), "text/x-shellscript"),
+        "bash" => (
+            "Bourne-Again shell script".to_string(),
+            "text/x-shellscript",
+        ),
+        "zsh" => (
+            "Paul Falstad's zsh script".to_string(),
+            "text/x-shellscript",
+        ),
+        "ksh" | "mksh" => ("Korn shell script".to_string(), "text/x-shellscript"),
+        "nxsh" => ("NexusShell script".to_string(), "text/x-shellscript"),
+        "python" => ("Python script".to_string(), "text/x-script.python"),
+        "perl" => ("Perl script".to_string(), "text/x-perl"),
+        "ruby" => ("Ruby script".to_string(), "text/x-ruby"),
+        "node" | "nodejs" => ("Node.js script".to_string(), "application/javascript"),
+        "awk" | "gawk" => ("awk script".to_string(), "text/x-awk"),
+        "" => ("a script".to_string(), "text/plain"),
+        _ => (format!("a {program} script"), "text/plain"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifies_binaries() {
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[4] = 2;
+        elf[5] = 1;
+        elf[16] = 3;
+        elf[18] = 0x3e;
+        let magic = identify(&elf);
+        assert_eq!(magic.description, "ELF 64-bit LSB shared object, x86-64");
+        assert_eq!(magic.mime, "application/x-sharedlib");
+
+        let mut pe = vec![0u8; 512];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x80;
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+        pe[0x98..0x9a].copy_from_slice(&0x20bu16.to_le_bytes());
+        pe[0xdc] = 3;
+        assert_eq!(
+            identify(&pe).description,
+            "PE32+ executable (console) x86-64, for MS Windows"
+        );
+        assert_eq!(identify(b"MZ").description, "MS-DOS executable");
+
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x10\0\0\0\x08";
+        assert_eq!(identify(png).description, "PNG image data, 16 x 8");
+        assert_eq!(identify(b"\x1f\x8b\x08\0").class, FileClass::Archive);
+        assert_eq!(
+            identify(b"%PDF-1.7\n").description,
+            "PDF document, version 1.7"
+        );
+        assert_eq!(identify(&[0, 1, 2, 3]).mime, "application/octet-stream");
+    }
+
+    #[test]
+    fn identifies_text() {
+        assert_eq!(identify(b"").description, "empty");
+        let plain = identify(b"hello\r\n");
+        assert_eq!(plain.description, "ASCII text, with CRLF line terminators");
+        assert_eq!((plain.mime, plain.charset), ("text/plain", "us-ascii"));
+        assert_eq!(
+            identify("caf\u{e9}\n".as_bytes()).description,
+            "Unicode text, UTF-8 text"
+        );
+        assert_eq!(identify(b"\xff\xfeh\0").charset, "utf-16le");
+
+        let script = identify(b"#!/usr/bin/env python3\nprint(1)\n");
+        assert_eq!(script.description, "Python script, ASCII text executable");
+        assert_eq!(script.mime, "text/x-script.python");
+        assert_eq!(
+            identify(b"#!/bin/sh\n").description,
+            "POSIX shell script, ASCII text executable"
+        );
+        assert_eq!(identify(b"<!DOCTYPE html>\n").mime, "text/html");
+    }
+}
@@ -0,0 +1,839 @@
+//! Mounted file systems, their space, and the block devices under them
+//!
+//! [`list_mounts`] reads `/proc/self/mountinfo` on Linux, falling back to
+//! the `getmntent` table in `/proc/self/mounts`, asks `getmntinfo` on macOS
+//! and walks the drive letters with the volume APIs on Windows.
+//! [`fs_usage`] gives the space and inodes of the file system holding a
+//! path, from `statvfs` or `GetDiskFreeSpaceExW`.
+//!
+//! [`list_block_devices`] reads the disks and partitions in
+//! `/sys/class/block` on Linux, taking file system types, UUIDs and labels
+//! from the udev database or the links in `/dev/disk`. Elsewhere only the
+//! volumes that are mounted can be listed.
+
+use crate::error::{HalError, HalResult};
+use std::path::{Path, PathBuf};
+
+/// One mounted file system as read by [`list_mounts`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    /// What is mounted: a device such as `/dev/sda1`, a share or a volume
+    /// name, or just a name for pseudo file systems
+    pub source: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// The options as `mount` shows them, `rw` or `ro` first
+    pub options: Vec<String>,
+    /// The device's major and minor number, where the system has them
+    pub device_number: Option<(u32, u32)>,
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+}
+
+impl MountEntry {
+    pub fn is_read_only(&self) -> bool {
+        self.options.iter().any(|option| option == "ro")
+    }
+}
+
+/// Space and inodes of a file system as read by [`fs_usage`], in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsUsage {
+    pub total: u64,
+    pub free: u64,
+    /// What unprivileged users may still fill, which leaves out the blocks
+    /// kept for the superuser
+    pub available: u64,
+    /// All and free inodes, where the file system counts them
+    pub files: Option<u64>,
+    pub files_free: Option<u64>,
+}
+
+impl FsUsage {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// The part in use as `df` shows it: used against what users may fill,
+    /// rounded up, or `None` for a file system without blocks
+    pub fn use_percent(&self) -> Option<u64> {
+        let usable = self.used() + self.available;
+        (usable > 0).then(|| (self.used() * 100).div_ceil(usable))
+    }
+
+    /// The part of the inodes in use, rounded up
+    pub fn inode_use_percent(&self) -> Option<u64> {
+        let files = self.files.filter(|&files| files > 0)?;
+        let used = files.saturating_sub(self.files_free.unwrap_or(0));
+        Some((used * 100).div_ceil(files))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockDeviceKind {
+    Disk,
+    Partition,
+    Loop,
+    Rom,
+    Raid,
+    /// A device mapper target, such as an LVM volume or an encrypted one
+    Mapped,
+}
+
+impl BlockDeviceKind {
+    /// The name `lsblk` prints
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockDeviceKind::Disk => "disk",
+            BlockDeviceKind::Partition => "part",
+            BlockDeviceKind::Loop => "loop",
+            BlockDeviceKind::Rom => "rom",
+            BlockDeviceKind::Raid => "raid",
+            BlockDeviceKind::Mapped => "dm",
+        }
+    }
+}
+
+/// One disk, partition or volume as read by [`list_block_devices`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDevice {
+    pub name: String,
+    /// The device file, such as `/dev/sda1` or `\\?\Volume{...}\`
+    pub path: PathBuf,
+    pub kind: BlockDeviceKind,
+    /// The name of the disk a partition is on
+    pub parent: Option<String>,
+    pub device_number: Option<(u32, u32)>,
+    /// Size in bytes
+    pub size: u64,
+    pub removable: bool,
+    pub read_only: bool,
+    pub model: Option<String>,
+    pub fs_type: Option<String>,
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+    pub mount_points: Vec<PathBuf>,
+}
+
+/// Every mounted file system, in the order they were mounted
+pub fn list_mounts() -> HalResult<Vec<MountEntry>> {
+    read_mounts()
+}
+
+/// The mount holding `path`: the one on its closest ancestor, and of
+/// several there the last mounted, which hides the others
+pub fn mount_containing<P: AsRef<Path>>(path: P) -> HalResult<Option<MountEntry>> {
+    let path = path.as_ref();
+    let path = path
+        .canonicalize()
+        .map_err(|e| HalError::io_error("canonicalize", Some(&path.display().to_string()), e))?;
+    // Canonical paths are verbatim (`\\?\C:\...`) on Windows, drive roots not
+    #[cfg(windows)]
+    let path = match path.to_str().and_then(|text| text.strip_prefix(r"\\?\")) {
+        Some(text) if !text.starts_with("UNC") => PathBuf::from(text),
+        _ => path,
+    };
+    Ok(list_mounts()?
+        .into_iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.components().count()))
+}
+
+/// Size, free space and inodes of the file system holding `path`
+pub fn fs_usage<P: AsRef<Path>>(path: P) -> HalResult<FsUsage> {
+    let path = path.as_ref();
+    #[cfg(unix)]
+    {
+        let stat = nix::sys::statvfs::statvfs(path).map_err(|e| {
+            HalError::io_error("statvfs", Some(&path.display().to_string()), e.into())
+        })?;
+        let block = stat.fragment_size() as u64;
+        Ok(FsUsage {
+            total: stat.blocks() as u64 * block,
+            free: stat.blocks_free() as u64 * block,
+            available: stat.blocks_available() as u64 * block,
+            files: Some(stat.files() as u64),
+            files_free: Some(stat.files_free() as u64),
+        })
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+        if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0
+        {
+            return Err(HalError::io_error(
+                "GetDiskFreeSpaceExW",
+                Some(&path.display().to_string()),
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(FsUsage {
+            total,
+            free,
+            available,
+            files: None,
+            files_free: None,
+        })
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Err(HalError::unsupported(
+            "File system usage is not supported on this platform",
+        ))
+    }
+}
+
+/// The disks with their partitions after each, and the other block
+/// devices, with the file systems on them and where those are mounted
+pub fn list_block_devices() -> HalResult<Vec<BlockDevice>> {
+    let mounts = list_mounts().unwrap_or_default();
+    let mut devices = read_block_devices(&mounts)?;
+    devices.sort_by_key(|device| device.device_number);
+    let (partitions, others): (Vec<_>, Vec<_>) = devices
+        .into_iter()
+        .partition(|device| device.parent.is_some());
+    let mut ordered = Vec::with_capacity(partitions.len() + others.len());
+    for device in others {
+        let name = device.name.clone();
+        ordered.push(device);
+        ordered.extend(
+            partitions
+                .iter()
+                .filter(|partition| partition.parent.as_deref() == Some(name.as_str()))
+                .cloned(),
+        );
+    }
+    // Partitions whose disk was not listed still show
+    for partition in partitions {
+        if !ordered.contains(&partition) {
+            ordered.push(partition);
+        }
+    }
+    Ok(ordered)
+}
+
+#[cfg(target_os = "linux")]
+fn read_mounts() -> HalResult<Vec<MountEntry>> {
+    let mut mounts: Vec<MountEntry> = match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(table) => table.lines().filter_map(parse_mountinfo_line).collect(),
+        Err(_) => std::fs::read_to_string("/proc/self/mounts")
+            .map_err(|e| HalError::io_error("read", Some("/proc/self/mounts"), e))?
+            .lines()
+            .filter_map(parse_mounts_line)
+            .collect(),
+    };
+    let ids = linux::VolumeIds::read();
+    for mount in &mut mounts {
+        let (_, uuid, label) = ids.lookup(mount.device_number, &linux::device_path(&mount.source));
+        mount.uuid = uuid;
+        mount.label = label;
+    }
+    Ok(mounts)
+}
+
+/// A line of `/proc/self/mountinfo`:
+/// `ID PARENT MAJOR:MINOR ROOT MOUNT_POINT OPTIONS [TAGS...] - TYPE SOURCE SUPER_OPTIONS`
+#[cfg(target_os = "linux")]
+fn parse_mountinfo_line(line: &str) -> Option<MountEntry> {
+    let (mount, filesystem) = line.split_once(" - ")?;
+    let fields: Vec<&str> = mount.split(' ').collect();
+    let (major, minor) = fields.get(2)?.split_once(':')?;
+    let mut options: Vec<String> = fields.get(5)?.split(',').map(str::to_string).collect();
+    let mut filesystem = filesystem.split(' ');
+    let fs_type = filesystem.next()?;
+    let source = filesystem.next()?;
+    // The file system's own options follow the mount's, as in /proc/mounts
+    for option in filesystem.next().unwrap_or_default().split(',') {
+        if !option.is_empty()
+            && option != "rw"
+            && option != "ro"
+            && !options.iter().any(|existing| existing == option)
+        {
+            options.push(option.to_string());
+        }
+    }
+    Some(MountEntry {
+        source: unescape_mount(source),
+        mount_point: mount_point(fields.get(4)?),
+        fs_type: unescape_mount(fs_type),
+        options,
+        device_number: Some((major.parse().ok()?, minor.parse().ok()?)),
+        uuid: None,
+        label: None,
+    })
+}
+
+/// A line of the table `getmntent` reads:
+/// `SOURCE MOUNT_POINT TYPE OPTIONS DUMP PASS`
+#[cfg(target_os = "linux")]
+fn parse_mounts_line(line: &str) -> Option<MountEntry> {
+    let mut fields = line.split_whitespace();
+    let source = fields.next()?;
+    let mount = fields.next()?;
+    let fs_type = fields.next()?;
+    let options = fields.next()?;
+    Some(MountEntry {
+        source: unescape_mount(source),
+        mount_point: mount_point(mount),
+        fs_type: unescape_mount(fs_type),
+        options: options.split(',').map(str::to_string).collect(),
+        device_number: None,
+        uuid: None,
+        label: None,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn mount_point(field: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(unescape_mount_bytes(field)))
+}
+
+#[cfg(target_os = "linux")]
+fn unescape_mount(field: &str) -> String {
+    String::from_utf8_lossy(&unescape_mount_bytes(field)).into_owned()
+}
+
+/// Undo the octal escapes (`\040` for a space) of the mount tables
+#[cfg(target_os = "linux")]
+fn unescape_mount_bytes(field: &str) -> Vec<u8> {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|code| std::str::from_utf8(code).ok())
+            .and_then(|code| u8::from_str_radix(code, 8).ok());
+        match code {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(target_os = "linux")]
+fn read_block_devices(mounts: &[MountEntry]) -> HalResult<Vec<BlockDevice>> {
+    let class = Path::new("/sys/class/block");
+    let entries = std::fs::read_dir(class)
+        .map_err(|e| HalError::io_error("read_dir", Some("/sys/class/block"), e))?;
+    let ids = linux::VolumeIds::read();
+    let sources: Vec<(PathBuf, &MountEntry)> = mounts
+        .iter()
+        .map(|mount| (linux::device_path(&mount.source), mount))
+        .collect();
+
+    let mut devices = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let sys = entry.path();
+        let read = |file: &str| {
+            std::fs::read_to_string(sys.join(file))
+                .ok()
+                .map(|text| text.trim().to_string())
+        };
+        let Some(number) = read("dev").and_then(|dev| {
+            let (major, minor) = dev.split_once(':')?;
+            Some((major.parse().ok()?, minor.parse().ok()?))
+        }) else {
+            continue;
+        };
+
+        // A partition's directory is inside its disk's
+        let parent = if sys.join("partition").exists() {
+            sys.canonicalize()
+                .ok()
+                .and_then(|real| Some(real.parent()?.file_name()?.to_str()?.to_string()))
+        } else {
+            None
+        };
+        let kind = if parent.is_some() {
+            BlockDeviceKind::Partition
+        } else if name.starts_with("loop") {
+            BlockDeviceKind::Loop
+        } else if name.starts_with("sr") {
+            BlockDeviceKind::Rom
+        } else if name.starts_with("md") {
+            BlockDeviceKind::Raid
+        } else if name.starts_with("dm-") {
+            BlockDeviceKind::Mapped
+        } else {
+            BlockDeviceKind::Disk
+        };
+        let path = PathBuf::from("/dev").join(&name);
+        // Mapped devices go by the name they were set up with
+        let (shown, shown_path) = match read("dm/name").filter(|_| kind == BlockDeviceKind::Mapped)
+        {
+            Some(mapped) => (mapped.clone(), PathBuf::from("/dev/mapper").join(mapped)),
+            None => (name.clone(), path.clone()),
+        };
+        let disk = parent
+            .as_ref()
+            .map_or(sys.clone(), |parent| class.join(parent));
+        let removable = std::fs::read_to_string(disk.join("removable"))
+            .is_ok_and(|removable| removable.trim() == "1");
+
+        let mounted: Vec<&MountEntry> = sources
+            .iter()
+            .filter(|(source, mount)| mount.device_number == Some(number) || *source == path)
+            .map(|(_, mount)| *mount)
+            .collect();
+        let (fs_type, uuid, label) = ids.lookup(Some(number), &path);
+        devices.push(BlockDevice {
+            name: shown,
+            path: shown_path,
+            kind,
+            parent,
+            device_number: Some(number),
+            // Counted in 512 byte sectors whatever the device's own
+            size: read("size")
+                .and_then(|sectors| sectors.parse::<u64>().ok())
+                .unwrap_or(0)
+                * 512,
+            removable,
+            read_only: read("ro").as_deref() == Some("1"),
+            model: read("device/model").filter(|model| !model.is_empty()),
+            fs_type: fs_type.or_else(|| mounted.first().map(|mount| mount.fs_type.clone())),
+            uuid,
+            label,
+            mount_points: mounted
+                .iter()
+                .map(|mount| mount.mount_point.clone())
+                .collect(),
+        });
+    }
+    Ok(devices)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    /// The device file a mount source names, with links such as
+    /// `/dev/mapper/root` or `/dev/disk/by-uuid/...` followed
+    pub(super) fn device_path(source: &str) -> PathBuf {
+        let path = PathBuf::from(source);
+        if source.starts_with("/dev/") {
+            path.canonicalize().unwrap_or(path)
+        } else {
+            path
+        }
+    }
+
+    /// The names udev gives devices by their file systems' UUIDs and labels
+    pub(super) struct VolumeIds {
+        uuids: HashMap<PathBuf, String>,
+        labels: HashMap<PathBuf, String>,
+    }
+
+    impl VolumeIds {
+        pub(super) fn read() -> Self {
+            VolumeIds {
+                uuids: disk_links("/dev/disk/by-uuid"),
+                labels: disk_links("/dev/disk/by-label"),
+            }
+        }
+
+        /// File system type, UUID and label of a device, from the udev
+        /// database or else the links in `/dev/disk`
+        pub(super) fn lookup(
+            &self,
+            number: Option<(u32, u32)>,
+            device: &Path,
+        ) -> (Option<String>, Option<String>, Option<String>) {
+            let properties = number.map(udev_properties).unwrap_or_default();
+            let property = |key: &str| properties.get(key).filter(|value| !value.is_empty());
+            let uuid = property("ID_FS_UUID")
+                .cloned()
+                .or_else(|| self.uuids.get(device).cloned());
+            let label = property("ID_FS_LABEL_ENC")
+                .map(|label| unescape_hex(label))
+                .or_else(|| property("ID_FS_LABEL").cloned())
+                .or_else(|| self.labels.get(device).cloned());
+            (property("ID_FS_TYPE").cloned(), uuid, label)
+        }
+    }
+
+    /// The device each link in `dir` leads to, with the link's name
+    fn disk_links(dir: &str) -> HashMap<PathBuf, String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return HashMap::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let device = entry.path().canonicalize().ok()?;
+                Some((device, unescape_hex(&entry.file_name().to_string_lossy())))
+            })
+            .collect()
+    }
+
+    /// The `E:KEY=VALUE` properties udev keeps for a block device
+    fn udev_properties((major, minor): (u32, u32)) -> HashMap<String, String> {
+        std::fs::read_to_string(format!("/run/udev/data/b{major}:{minor}"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Undo udev's `\x20` escapes
+    pub(super) fn unescape_hex(text: &str) -> String {
+        let mut out = Vec::with_capacity(text.len());
+        let mut rest = text.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            let code = (byte == b'\\' && tail.first() == Some(&b'x'))
+                .then(|| tail.get(1..3))
+                .flatten()
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match code {
+                Some(decoded) => {
+                    out.push(decoded);
+                    rest = &tail[3..];
+                }
+                None => {
+                    out.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn read_mounts() -> HalResult<Vec<MountEntry>> {
+    use nix::libc;
+    use std::ffi::CStr;
+
+    let mut table: *mut libc::statfs = std::ptr::null_mut();
+    // The table belongs to the C library and stays until the next call
+    let count = unsafe { libc::getmntinfo(&mut table, libc::MNT_NOWAIT) };
+    if count <= 0 || table.is_null() {
+        return Err(HalError::io_error(
+            "getmntinfo",
+            None,
+            std::io::Error::last_os_error(),
+        ));
+    }
+    let entries = unsafe { std::slice::from_raw_parts(table, count as usize) };
+    let text = |field: &[libc::c_char]| {
+        unsafe { CStr::from_ptr(field.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let flags = entry.f_flags;
+            let mut options = vec![if flags & libc::MNT_RDONLY as u32 != 0 {
+                "ro".to_string()
+            } else {
+                "rw".to_string()
+            }];
+            for (flag, name) in [
+                (libc::MNT_SYNCHRONOUS, "sync"),
+                (libc::MNT_ASYNC, "async"),
+                (libc::MNT_NOEXEC, "noexec"),
+                (libc::MNT_NOSUID, "nosuid"),
+                (libc::MNT_NODEV, "nodev"),
+                (libc::MNT_NOATIME, "noatime"),
+                (libc::MNT_LOCAL, "local"),
+                (libc::MNT_JOURNALED, "journaled"),
+                (libc::MNT_DONTBROWSE, "nobrowse"),
+            ] {
+                if flags & flag as u32 != 0 {
+                    options.push(name.to_string());
+                }
+            }
+            let source = text(&entry.f_mntfromname);
+            // The device's number is that of the device file it was mounted from
+            let device_number = source
+                .starts_with("/dev/")
+                .then(|| nix::sys::stat::stat(source.as_str()).ok())
+                .flatten()
+                .map(|stat| {
+                    let device = stat.st_rdev as u32;
+                    (device >> 24, device & 0x00ff_ffff)
+                });
+            MountEntry {
+                source,
+                mount_point: PathBuf::from(text(&entry.f_mntonname)),
+                fs_type: text(&entry.f_fstypename),
+                options,
+                device_number,
+                uuid: None,
+                label: None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+fn read_mounts() -> HalResult<Vec<MountEntry>> {
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDriveTypeW, GetLogicalDriveStringsW, GetVolumeInformationW,
+        GetVolumeNameForVolumeMountPointW,
+    };
+    // From winbase.h and winnt.h
+    const DRIVE_TYPES: [&str; 7] = [
+        "unknown",
+        "noroot",
+        "removable",
+        "fixed",
+        "remote",
+        "cdrom",
+        "ramdisk",
+    ];
+    const FILE_READ_ONLY_VOLUME: u32 = 0x0008_0000;
+
+    let mut buffer = [0u16; 512];
+    let len = unsafe { GetLogicalDriveStringsW(buffer.len() as u32, buffer.as_mut_ptr()) };
+    if len == 0 || len as usize > buffer.len() {
+        return Err(HalError::io_error(
+            "GetLogicalDriveStringsW",
+            None,
+            std::io::Error::last_os_error(),
+        ));
+    }
+    let text = |wide: &[u16]| {
+        let end = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+        String::from_utf16_lossy(&wide[..end])
+    };
+
+    let mut mounts = Vec::new();
+    // `C:\`, `D:\` and so on, each ending in a nul
+    for root in buffer[..len as usize].split(|&c| c == 0) {
+        if root.is_empty() {
+            continue;
+        }
+        let root: Vec<u16> = root.iter().copied().chain(Some(0)).collect();
+        let (mut name, mut fs_name) = ([0u16; 261], [0u16; 261]);
+        let (mut serial, mut max_component, mut flags) = (0u32, 0u32, 0u32);
+        // Drives without a medium in them have no volume to show
+        if unsafe {
+            GetVolumeInformationW(
+                root.as_ptr(),
+                name.as_mut_ptr(),
+                name.len() as u32,
+                &mut serial,
+                &mut max_component,
+                &mut flags,
+                fs_name.as_mut_ptr(),
+                fs_name.len() as u32,
+            )
+        } == 0
+        {
+            continue;
+        }
+        let mut volume = [0u16; 64];
+        let root_text = text(&root);
+        let source = if unsafe {
+            GetVolumeNameForVolumeMountPointW(
+                root.as_ptr(),
+                volume.as_mut_ptr(),
+                volume.len() as u32,
+            )
+        } != 0
+        {
+            text(&volume)
+        } else {
+            root_text.clone()
+        };
+        let drive_type = unsafe { GetDriveTypeW(root.as_ptr()) } as usize;
+        let mut options = vec![if flags & FILE_READ_ONLY_VOLUME != 0 {
+            "ro".to_string()
+        } else {
+            "rw".to_string()
+        }];
+        options.push(
+            DRIVE_TYPES
+                .get(drive_type)
+                .unwrap_or(&"unknown")
+                .to_string(),
+        );
+        let label = text(&name);
+        mounts.push(MountEntry {
+            source,
+            mount_point: PathBuf::from(root_text),
+            fs_type: text(&fs_name),
+            options,
+            device_number: None,
+            // The serial number, as `vol` shows it
+            uuid: Some(format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff)),
+            label: (!label.is_empty()).then_some(label),
+        });
+    }
+    Ok(mounts)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", windows)))]
+fn read_mounts() -> HalResult<Vec<MountEntry>> {
+    Err(HalError::unsupported(
+        "Mount listing is not supported on this platform",
+    ))
+}
+
+/// Where the system has no table of block devices, the mounted volumes
+#[cfg(not(target_os = "linux"))]
+fn read_block_devices(mounts: &[MountEntry]) -> HalResult<Vec<BlockDevice>> {
+    let mut devices: Vec<BlockDevice> = Vec::new();
+    for mount in mounts {
+        let is_volume = if cfg!(windows) {
+            mount.source.starts_with(r"\\?\Volume")
+        } else {
+            mount.source.starts_with("/dev/")
+        };
+        if !is_volume {
+            continue;
+        }
+        let path = PathBuf::from(&mount.source);
+        if let Some(device) = devices.iter_mut().find(|device| device.path == path) {
+            device.mount_points.push(mount.mount_point.clone());
+            continue;
+        }
+        let name = if cfg!(windows) {
+            mount
+                .mount_point
+                .display()
+                .to_string()
+                .trim_end_matches('\\')
+                .to_string()
+        } else {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| mount.source.clone())
+        };
+        let has = |option: &str| mount.options.iter().any(|existing| existing == option);
+        devices.push(BlockDevice {
+            name,
+            path,
+            kind: if has("cdrom") {
+                BlockDeviceKind::Rom
+            } else {
+                BlockDeviceKind::Partition
+            },
+            parent: None,
+            device_number: mount.device_number,
+            size: fs_usage(&mount.mount_point).map_or(0, |usage| usage.total),
+            removable: has("removable"),
+            read_only: mount.is_read_only(),
+            model: None,
+            fs_type: Some(mount.fs_type.clone()),
+            uuid: mount.uuid.clone(),
+            label: mount.label.clone(),
+            mount_points: vec![mount.mount_point.clone()],
+        });
+    }
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_percentages() {
+        let usage = FsUsage {
+            total: 1000,
+            free: 300,
+            available: 200,
+            files: Some(64),
+            files_free: Some(15),
+        };
+        assert_eq!(usage.used(), 700);
+        // 700 of the 900 users may have, rounded up
+        assert_eq!(usage.use_percent(), Some(78));
+        assert_eq!(usage.inode_use_percent(), Some(77));
+
+        let empty = FsUsage {
+            total: 0,
+            free: 0,
+            available: 0,
+            files: Some(0),
+            files_free: Some(0),
+        };
+        assert_eq!(empty.use_percent(), None);
+        assert_eq!(empty.inode_use_percent(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_mountinfo_line() {
+        let line = "36 35 98:0 / /mnt/my\\040disk rw,noatime shared:1 master:2 - ext4 \
+                    /dev/sda1 rw,errors=remount-ro";
+        let mount = parse_mountinfo_line(line).unwrap();
+        assert_eq!(mount.source, "/dev/sda1");
+        assert_eq!(mount.mount_point, Path::new("/mnt/my disk"));
+        assert_eq!(mount.fs_type, "ext4");
+        assert_eq!(mount.options, vec!["rw", "noatime", "errors=remount-ro"]);
+        assert_eq!(mount.device_number, Some((98, 0)));
+        assert!(!mount.is_read_only());
+
+        let line = "24 28 0:23 / /sys ro,nosuid - sysfs sysfs rw";
+        let mount = parse_mountinfo_line(line).unwrap();
+        assert_eq!(mount.options, vec!["ro", "nosuid"]);
+        assert!(mount.is_read_only());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_mounts_line() {
+        let mount = parse_mounts_line("/dev/sda1 / ext4 rw,relatime 0 1").unwrap();
+        assert_eq!(mount.source, "/dev/sda1");
+        assert_eq!(mount.mount_point, Path::new("/"));
+        assert_eq!(mount.fs_type, "ext4");
+        assert_eq!(mount.options, vec!["rw", "relatime"]);
+        assert_eq!(mount.device_number, None);
+        assert_eq!(linux::unescape_hex("my\\x20disk"), "my disk");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_temp_dir_is_on_a_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let mount = mount_containing(dir.path()).unwrap().expect("a mount");
+        assert!(dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .starts_with(&mount.mount_point));
+        assert!(list_mounts()
+            .unwrap()
+            .iter()
+            .any(|mount| mount.mount_point == Path::new("/")));
+
+        let usage = fs_usage(dir.path()).unwrap();
+        assert!(usage.available <= usage.total);
+        assert!(usage.free <= usage.total);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_partitions_follow_their_disk() {
+        let Ok(devices) = list_block_devices() else {
+            return;
+        };
+        for (i, device) in devices.iter().enumerate() {
+            if let Some(parent) = &device.parent {
+                assert!(devices[..i].iter().any(|disk| &disk.name == parent));
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use crate::error::NetworkError;
 use crate::{Capabilities, HalError, HalResult, Platform}; // HalError 実際に使用されていたため復元
 use std::collections::HashMap;
-use std::net::{IpAddr, SocketAddr};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket,
+};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, SystemTime};
 
@@ -43,6 +46,46 @@ pub enum ConnectionTarget {
     Host { host: String, port: u16 },
 }
 
+impl ConnectionTarget {
+    /// The addresses the target may be reached at, in the resolver's order
+    pub fn resolve(&self) -> HalResult<Vec<SocketAddr>> {
+        match self {
+            ConnectionTarget::Address(addr) => Ok(vec![*addr]),
+            ConnectionTarget::Host { host, port } => {
+                let addrs: Vec<SocketAddr> = (host.as_str(), *port)
+                    .to_socket_addrs()
+                    .map_err(|e| network_error("resolve", self, &e))?
+                    .collect();
+                if addrs.is_empty() {
+                    return Err(network_error(
+                        "resolve",
+                        self,
+                        &std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            "Name or service not known",
+                        ),
+                    ));
+                }
+                Ok(addrs)
+            }
+        }
+    }
+
+    fn host(&self) -> String {
+        match self {
+            ConnectionTarget::Address(addr) => addr.ip().to_string(),
+            ConnectionTarget::Host { host, .. } => host.clone(),
+        }
+    }
+
+    fn port(&self) -> u16 {
+        match self {
+            ConnectionTarget::Address(addr) => addr.port(),
+            ConnectionTarget::Host { port, .. } => *port,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum NetworkProtocol {
     Tcp,
@@ -452,6 +495,65 @@ impl NetworkManager {
     }
 }
 
+/// Connect to the first address of `target` that accepts, giving up on
+/// each after `timeout` when one is set
+pub fn connect_tcp(target: &ConnectionTarget, timeout: Option<Duration>) -> HalResult<TcpStream> {
+    let mut last = None;
+    for addr in target.resolve()? {
+        let stream = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(addr),
+        };
+        match stream {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(network_error(
+        "connect",
+        target,
+        &last.unwrap_or_else(|| std::io::ErrorKind::NotFound.into()),
+    ))
+}
+
+/// A UDP socket on an ephemeral port of the target's family, connected to
+/// its first address
+pub fn connect_udp(target: &ConnectionTarget) -> HalResult<UdpSocket> {
+    let addr = target.resolve()?[0];
+    let socket = bind_udp(SocketAddr::new(unspecified(addr.ip()), 0))?;
+    socket
+        .connect(addr)
+        .map_err(|e| network_error("connect", target, &e))?;
+    Ok(socket)
+}
+
+/// A TCP listener on `addr`, port 0 picking a free one
+pub fn listen_tcp(addr: SocketAddr) -> HalResult<TcpListener> {
+    TcpListener::bind(addr).map_err(|e| network_error("bind", &ConnectionTarget::Address(addr), &e))
+}
+
+/// A UDP socket bound to `addr`, port 0 picking a free one
+pub fn bind_udp(addr: SocketAddr) -> HalResult<UdpSocket> {
+    UdpSocket::bind(addr).map_err(|e| network_error("bind", &ConnectionTarget::Address(addr), &e))
+}
+
+/// The wildcard address of the family of `ip`
+fn unspecified(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+fn network_error(operation: &str, target: &ConnectionTarget, err: &std::io::Error) -> HalError {
+    HalError::Network(NetworkError {
+        operation: operation.to_string(),
+        address: Some(target.host()),
+        port: Some(target.port()),
+        message: err.to_string(),
+    })
+}
+
 impl Default for ConnectionPool {
     fn default() -> Self {
         Self::new()
@@ -556,3 +658,52 @@ mod uuid {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn connects_to_a_listener_over_tcp_and_udp() {
+        let listener = listen_tcp("127.0.0.1:0".parse().unwrap()).unwrap();
+        let target = ConnectionTarget::Host {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+        };
+        let mut client = connect_tcp(&target, Some(Duration::from_secs(5))).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        client.write_all(b"ping").unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        let server = bind_udp("127.0.0.1:0".parse().unwrap()).unwrap();
+        let client = connect_udp(&ConnectionTarget::Address(server.local_addr().unwrap())).unwrap();
+        client.send(b"pong").unwrap();
+        let (n, from) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"pong");
+        assert_eq!(from, client.local_addr().unwrap());
+    }
+
+    #[test]
+    fn reports_the_target_of_a_refused_connection() {
+        let port = listen_tcp("127.0.0.1:0".parse().unwrap())
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let target = ConnectionTarget::Host {
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+        match connect_tcp(&target, None) {
+            Err(HalError::Network(err)) => {
+                assert_eq!(err.operation, "connect");
+                assert_eq!(err.address.as_deref(), Some("127.0.0.1"));
+                assert_eq!(err.port, Some(port));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
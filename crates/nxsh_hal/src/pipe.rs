@@ -71,6 +71,36 @@ pub fn pipe_nonblock() -> std::io::Result<(std::fs::File, std::fs::File)> {
     ))
 }
 
+/// A blocking pipe for connecting processes, as `(read end, write end)`.
+///
+/// Both ends are close-on-exec: a child only gets the end handed to it as
+/// stdin or stdout, so a reader sees end of file once every writer it was
+/// given to has exited.
+#[cfg(unix)]
+pub fn stream_pipe() -> std::io::Result<(std::fs::File, std::fs::File)> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+    let (read_fd, write_fd) = pipe()?;
+    // Take ownership first so both ends are closed if setting a flag fails
+    let ends = unsafe {
+        (
+            std::fs::File::from_raw_fd(read_fd),
+            std::fs::File::from_raw_fd(write_fd),
+        )
+    };
+    fcntl(read_fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    fcntl(write_fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    Ok(ends)
+}
+
+#[cfg(windows)]
+pub fn stream_pipe() -> std::io::Result<(std::fs::File, std::fs::File)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "stream_pipe not yet supported on Windows",
+    ))
+}
+
 /// Handle to a pipe
 #[derive(Debug)]
 pub struct PipeHandle {
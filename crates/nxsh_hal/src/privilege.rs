@@ -0,0 +1,413 @@
+//! Running commands with administrative rights
+//!
+//! Unix has no system call for gaining privileges, so elevation is
+//! delegated to a launcher found in `PATH`: `sudo`, `doas` or polkit's
+//! `pkexec`, each of which asks for credentials its own way. Windows asks
+//! through the UAC consent prompt by starting the program with the
+//! `runas` verb of `ShellExecuteEx`; such a program runs in a console of
+//! its own, so its output cannot be captured.
+
+use crate::command::{find_in_path, CommandResult};
+use crate::error::{HalError, HalResult};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// Whether the current process already has administrative rights: an
+/// effective user ID of 0 on Unix, an elevated token on Windows
+pub fn is_elevated() -> bool {
+    #[cfg(unix)]
+    {
+        nix::unistd::geteuid().is_root()
+    }
+    #[cfg(windows)]
+    {
+        token_is_elevated().unwrap_or(false)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        false
+    }
+}
+
+/// A way of gaining administrative rights
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sudo,
+    Doas,
+    Pkexec,
+    /// The Windows consent prompt
+    Uac,
+}
+
+impl Backend {
+    /// The backends this platform offers, most preferred first
+    pub fn for_platform() -> &'static [Backend] {
+        if cfg!(windows) {
+            &[Backend::Uac]
+        } else {
+            &[Backend::Sudo, Backend::Doas, Backend::Pkexec]
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Sudo => "sudo",
+            Backend::Doas => "doas",
+            Backend::Pkexec => "pkexec",
+            Backend::Uac => "uac",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Backend::Sudo, Backend::Doas, Backend::Pkexec, Backend::Uac]
+            .into_iter()
+            .find(|backend| backend.name() == name)
+    }
+
+    /// Find the backend's launcher in a `PATH`-style list. UAC needs
+    /// none and is found on Windows only.
+    pub fn locate(self, path: &OsStr) -> Option<Launcher> {
+        if !Self::for_platform().contains(&self) {
+            return None;
+        }
+        let program = match self {
+            Backend::Uac => None,
+            other => Some(find_in_path(other.name(), path)?),
+        };
+        Some(Launcher {
+            backend: self,
+            program,
+        })
+    }
+}
+
+/// The first backend of this platform that is available
+pub fn find_launcher(path: &OsStr) -> Option<Launcher> {
+    Backend::for_platform()
+        .iter()
+        .find_map(|backend| backend.locate(path))
+}
+
+/// An available backend, with its launcher's full path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launcher {
+    backend: Backend,
+    program: Option<PathBuf>,
+}
+
+impl Launcher {
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+}
+
+/// A program to run with administrative rights
+#[derive(Debug, Clone)]
+pub struct Elevation {
+    launcher: Launcher,
+    program: OsString,
+    args: Vec<OsString>,
+    user: Option<String>,
+    prompt: Option<String>,
+    dir: Option<PathBuf>,
+}
+
+impl Elevation {
+    /// Run `program` through `launcher`. `pkexec` needs a full path.
+    pub fn new<S: AsRef<OsStr>>(launcher: Launcher, program: S) -> Self {
+        Self {
+            launcher,
+            program: program.as_ref().to_os_string(),
+            args: Vec::new(),
+            user: None,
+            prompt: None,
+            dir: None,
+        }
+    }
+
+    /// Add arguments for the program
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// Run as USER rather than the administrator; not possible with UAC
+    pub fn user(&mut self, user: &str) -> &mut Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// The password prompt, where the backend lets it be chosen
+    pub fn prompt(&mut self, prompt: &str) -> &mut Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+
+    /// Set the working directory for the program
+    pub fn current_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Run the program and wait for it. Output is captured from Unix
+    /// launchers, while credentials are asked for on the terminal; a
+    /// program started through UAC has no output to capture.
+    pub fn output(&self) -> HalResult<CommandResult> {
+        #[cfg(unix)]
+        {
+            let output = self
+                .unix_command()?
+                .stdin(std::process::Stdio::inherit())
+                .output()
+                .map_err(|e| HalError::io_error("elevate", self.program.to_str(), e))?;
+            let code = std::os::unix::process::ExitStatusExt::signal(&output.status)
+                .map(|signal| 128 + signal)
+                .or(output.status.code())
+                .unwrap_or(1);
+            Ok(CommandResult::new(code, output.stdout, output.stderr))
+        }
+        #[cfg(windows)]
+        {
+            let code = self.shell_execute(true)?.unwrap_or(0);
+            Ok(CommandResult::new(code, Vec::new(), Vec::new()))
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            Err(HalError::unsupported("elevation on this platform"))
+        }
+    }
+
+    /// Hand over to the program. On Unix the current process is replaced
+    /// and this returns only on failure; on Windows the program is started
+    /// in a new window and this returns at once.
+    pub fn exec(&self) -> HalResult<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let error = self.unix_command()?.exec();
+            Err(HalError::io_error("elevate", self.program.to_str(), error))
+        }
+        #[cfg(windows)]
+        {
+            self.shell_execute(false).map(|_| ())
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            Err(HalError::unsupported("elevation on this platform"))
+        }
+    }
+
+    /// The launcher's command line
+    #[cfg(unix)]
+    fn unix_command(&self) -> HalResult<std::process::Command> {
+        let Some(launcher) = &self.launcher.program else {
+            return Err(HalError::unsupported("UAC elevation outside Windows"));
+        };
+        let mut command = std::process::Command::new(launcher);
+        match self.launcher.backend {
+            Backend::Sudo => {
+                if let Some(prompt) = &self.prompt {
+                    command.arg("-p").arg(prompt);
+                }
+                if let Some(user) = &self.user {
+                    command.arg("-u").arg(user);
+                }
+                command.arg("--");
+            }
+            Backend::Doas => {
+                if let Some(user) = &self.user {
+                    command.arg("-u").arg(user);
+                }
+                command.arg("--");
+            }
+            Backend::Pkexec => {
+                if let Some(user) = &self.user {
+                    command.arg("--user").arg(user);
+                }
+            }
+            Backend::Uac => unreachable!("UAC has no launcher"),
+        }
+        command.arg(&self.program).args(&self.args);
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+        Ok(command)
+    }
+
+    /// Start the program through the UAC prompt, returning its exit code
+    /// when `wait` is set
+    #[cfg(windows)]
+    fn shell_execute(&self, wait: bool) -> HalResult<Option<i32>> {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Foundation::{CloseHandle, ERROR_CANCELLED};
+        use windows_sys::Win32::System::Threading::{
+            GetExitCodeProcess, WaitForSingleObject, INFINITE,
+        };
+        use windows_sys::Win32::UI::Shell::{
+            ShellExecuteExW, SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW,
+        };
+        use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+        if self.user.is_some() {
+            return Err(HalError::unsupported("running as another user through UAC"));
+        }
+        fn wide(text: &OsStr) -> Vec<u16> {
+            text.encode_wide().chain(std::iter::once(0)).collect()
+        }
+        let verb = wide(OsStr::new("runas"));
+        let file = wide(&self.program);
+        let parameters = wide(&windows_command_line(&self.args));
+        let dir = self.dir.as_ref().map(|dir| wide(dir.as_os_str()));
+
+        // SAFETY: all-zero is a valid SHELLEXECUTEINFOW before it is filled in
+        let mut info: SHELLEXECUTEINFOW = unsafe { std::mem::zeroed() };
+        info.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC;
+        info.lpVerb = verb.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = parameters.as_ptr();
+        info.lpDirectory = dir.as_ref().map_or(std::ptr::null(), |dir| dir.as_ptr());
+        info.nShow = SW_SHOWNORMAL;
+
+        // SAFETY: every string pointer refers to a buffer alive for the call
+        if unsafe { ShellExecuteExW(&mut info) } == 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_CANCELLED as i32) {
+                return Err(HalError::security_error(
+                    "ShellExecuteExW",
+                    "administrator",
+                    "the elevation request was declined",
+                ));
+            }
+            return Err(HalError::process_error(
+                "ShellExecuteExW",
+                None,
+                &error.to_string(),
+            ));
+        }
+        let process = info.hProcess;
+        if process == 0 {
+            return Ok(None);
+        }
+        let mut code = None;
+        if wait {
+            let mut exit_code = 0u32;
+            // SAFETY: the process handle was returned by ShellExecuteExW
+            unsafe {
+                WaitForSingleObject(process, INFINITE);
+                if GetExitCodeProcess(process, &mut exit_code) != 0 {
+                    code = Some(exit_code as i32);
+                }
+            }
+        }
+        // SAFETY: the handle is ours to close, exactly once
+        unsafe { CloseHandle(process) };
+        Ok(code)
+    }
+}
+
+/// Join arguments into one command line the way the C runtime splits it
+#[cfg(any(windows, test))]
+fn windows_command_line(args: &[OsString]) -> OsString {
+    let mut line = String::new();
+    for arg in args {
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        let arg = arg.to_string_lossy();
+        if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+            line.push_str(&arg);
+            continue;
+        }
+        line.push('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    // Backslashes before a quote, and the quote, are escaped
+                    line.push_str(&"\\".repeat(backslashes * 2 + 1));
+                    line.push('"');
+                    backslashes = 0;
+                }
+                _ => {
+                    line.push_str(&"\\".repeat(backslashes));
+                    line.push(c);
+                    backslashes = 0;
+                }
+            }
+        }
+        line.push_str(&"\\".repeat(backslashes * 2));
+        line.push('"');
+    }
+    line.into()
+}
+
+#[cfg(windows)]
+fn token_is_elevated() -> Option<bool> {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let mut token: HANDLE = 0;
+    // SAFETY: the pseudo handle of the current process is always valid
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return None;
+    }
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut size = 0u32;
+    // SAFETY: the buffer and its size describe `elevation`
+    let ok = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut _ as *mut std::ffi::c_void,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        )
+    };
+    // SAFETY: the token handle is ours to close
+    unsafe { CloseHandle(token) };
+    (ok != 0).then_some(elevation.TokenIsElevated != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_windows_arguments() {
+        let args: Vec<OsString> = ["plain", "two words", r#"say "hi""#, r"a dir\", ""]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(
+            windows_command_line(&args),
+            OsString::from(r#"plain "two words" "say \"hi\"" "a dir\\" """#)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sudo_command_line_carries_user_and_prompt() {
+        let launcher = Launcher {
+            backend: Backend::Sudo,
+            program: Some(PathBuf::from("/usr/bin/sudo")),
+        };
+        let mut elevation = Elevation::new(launcher, "id");
+        elevation.args(["-u"]).user("backup").prompt("password: ");
+        let command = elevation.unix_command().unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            ["-p", "password: ", "-u", "backup", "--", "id", "-u"].map(OsStr::new)
+        );
+    }
+}
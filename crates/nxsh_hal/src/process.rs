@@ -9,7 +9,7 @@ use std::ffi::OsStr;
 use std::path::Path;
 use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 /// Process identifier type
 pub type ProcessId = u32;
@@ -49,6 +49,20 @@ pub enum ProcessStatus {
     Unknown,
 }
 
+impl ProcessStatus {
+    /// The one-letter state `ps` and `top` print
+    pub fn code(&self) -> char {
+        match self {
+            ProcessStatus::Running => 'R',
+            ProcessStatus::Sleeping => 'S',
+            ProcessStatus::Stopped => 'T',
+            ProcessStatus::Zombie => 'Z',
+            ProcessStatus::Exited(_) | ProcessStatus::Signaled(_) => 'X',
+            ProcessStatus::Unknown => '?',
+        }
+    }
+}
+
 /// Process handle for managing spawned processes
 ///
 /// This struct provides a high-level interface for process management,
@@ -747,6 +761,1299 @@ impl Default for ProcessManager {
     }
 }
 
+/// A change in a job process's state as reported by the operating system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildState {
+    /// The process exited with the given code
+    Exited(i32),
+    /// The process was killed by the given signal
+    Signaled(i32),
+    /// The process was stopped by the given signal (Ctrl-Z, SIGSTOP, ...)
+    Stopped(i32),
+    /// A stopped process was resumed with SIGCONT
+    Continued,
+}
+
+/// Prepare a command to run as part of a job.
+///
+/// On Unix the child joins process group `pgid` (0 makes it the leader of a
+/// new group) and gets the default dispositions for the job control signals
+/// an interactive shell ignores, so Ctrl-C and Ctrl-Z reach it again.
+#[cfg(unix)]
+pub fn prepare_job_command(command: &mut Command, pgid: ProcessGroupId) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(pgid as i32);
+    reset_job_signals(command);
+}
+
+/// Restore the default dispositions of the job control signals in the
+/// program `command` starts
+#[cfg(unix)]
+pub(crate) fn reset_job_signals(command: &mut Command) {
+    use nix::sys::signal::{signal, SigHandler, Signal};
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: only async-signal-safe sigaction calls run between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            for sig in [
+                Signal::SIGINT,
+                Signal::SIGQUIT,
+                Signal::SIGTSTP,
+                Signal::SIGTTIN,
+                Signal::SIGTTOU,
+            ] {
+                signal(sig, SigHandler::SigDfl)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Prepare a command to run as part of a job (no process groups on Windows;
+/// see [`JobObject`] for grouping processes instead)
+#[cfg(windows)]
+pub fn prepare_job_command(_command: &mut Command, _pgid: ProcessGroupId) {}
+
+/// Replace the current process with the program `command` runs, as the
+/// shell's `exec` does. Returns only if the program could not be started.
+#[cfg(unix)]
+pub fn replace_process(command: &mut Command) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+
+    // The program must not inherit the signals an interactive shell ignores
+    reset_job_signals(command);
+    command.exec()
+}
+
+/// Windows cannot replace a process image: run the program on the shell's
+/// console and exit with its status instead
+#[cfg(windows)]
+pub fn replace_process(command: &mut Command) -> std::io::Error {
+    match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    }
+}
+
+/// The nice value that gets the most favourable scheduling
+pub const NICE_MIN: i32 = -20;
+
+/// The nice value of programs that should only run when nothing else wants
+/// the CPU
+pub const NICE_MAX: i32 = 19;
+
+/// How the operating system schedules a program: its nice value and the
+/// CPUs it may run on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scheduling {
+    /// From [`NICE_MIN`] to [`NICE_MAX`]; Windows uses the nearest priority
+    /// class
+    pub nice: Option<i32>,
+    /// CPUs the program is pinned to, numbered from 0
+    pub cpus: Option<Vec<usize>>,
+}
+
+impl Scheduling {
+    /// Fail for CPUs out of range or not available to the shell, or for
+    /// CPUs on a platform that cannot pin programs to them
+    pub fn check(&self) -> HalResult<()> {
+        let Some(cpus) = &self.cpus else {
+            return Ok(());
+        };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use nix::sched::sched_getaffinity;
+            use nix::unistd::Pid;
+
+            cpu_set(cpus)?;
+            let available = sched_getaffinity(Pid::from_raw(0))
+                .map_err(|e| HalError::process_error("sched_getaffinity", None, e.desc()))?;
+            let missing = cpus
+                .iter()
+                .find(|&&cpu| !available.is_set(cpu).unwrap_or(false));
+            match missing {
+                Some(cpu) => Err(HalError::invalid(&format!("CPU {cpu} is not available"))),
+                None => Ok(()),
+            }
+        }
+        #[cfg(windows)]
+        {
+            windows_priority::affinity_mask(cpus).map(drop)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+        {
+            let _ = cpus;
+            Err(HalError::unsupported("CPU affinity on this platform"))
+        }
+    }
+
+    /// Arrange for the program `command` starts to be scheduled this way,
+    /// as far as [`Scheduling::check`] allows.
+    ///
+    /// The child changes its own scheduling before exec, so the program
+    /// never runs otherwise.
+    #[cfg(unix)]
+    pub fn prepare(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let nice = self.nice;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let cpus = self.cpus.as_deref().and_then(|cpus| cpu_set(cpus).ok());
+        // SAFETY: only the setpriority and sched_setaffinity system calls run
+        // between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(nice) = nice {
+                    unix_priority::set(0, nice)?;
+                }
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                if let Some(cpus) = &cpus {
+                    nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(0), cpus)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Arrange for the program `command` starts to be scheduled this way.
+    ///
+    /// The program is created in the priority class for the nice value;
+    /// pinning it to CPUs takes [`Scheduling::apply`] once it has started.
+    #[cfg(windows)]
+    pub fn prepare(&self, command: &mut Command) {
+        use std::os::windows::process::CommandExt;
+
+        if let Some(nice) = self.nice {
+            command.creation_flags(windows_priority::class(nice));
+        }
+    }
+
+    /// Change the scheduling of the running process `pid`, 0 being the
+    /// shell itself
+    pub fn apply(&self, pid: ProcessId) -> HalResult<()> {
+        if let Some(nice) = self.nice {
+            set_priority(pid, nice)?;
+        }
+        if let Some(cpus) = &self.cpus {
+            set_affinity(pid, cpus)?;
+        }
+        Ok(())
+    }
+}
+
+/// The nice value of process `pid`, 0 being the shell itself. On Windows it
+/// stands for the process's priority class.
+pub fn priority(pid: ProcessId) -> HalResult<i32> {
+    #[cfg(unix)]
+    {
+        unix_priority::get(pid)
+            .map_err(|e| HalError::process_error("getpriority", Some(pid), e.desc()))
+    }
+    #[cfg(windows)]
+    {
+        windows_priority::get(pid)
+    }
+}
+
+/// Set the nice value of process `pid`, 0 being the shell itself. Values
+/// past [`NICE_MIN`] or [`NICE_MAX`] are clamped; lowering the value usually
+/// needs privileges.
+pub fn set_priority(pid: ProcessId, nice: i32) -> HalResult<()> {
+    let nice = nice.clamp(NICE_MIN, NICE_MAX);
+    #[cfg(unix)]
+    {
+        unix_priority::set(pid, nice)
+            .map_err(|e| HalError::process_error("setpriority", Some(pid), e.desc()))
+    }
+    #[cfg(windows)]
+    {
+        windows_priority::set(pid, nice)
+    }
+}
+
+/// Pin process `pid`, 0 being the shell itself, to the given CPUs
+pub fn set_affinity(pid: ProcessId, cpus: &[usize]) -> HalResult<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(pid as i32), &cpu_set(cpus)?)
+            .map_err(|e| HalError::process_error("sched_setaffinity", Some(pid), e.desc()))
+    }
+    #[cfg(windows)]
+    {
+        windows_priority::set_affinity(pid, cpus)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    {
+        let _ = (pid, cpus);
+        Err(HalError::unsupported("CPU affinity on this platform"))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cpu_set(cpus: &[usize]) -> HalResult<nix::sched::CpuSet> {
+    let mut set = nix::sched::CpuSet::new();
+    for &cpu in cpus {
+        set.set(cpu)
+            .map_err(|_| HalError::invalid(&format!("CPU {cpu} is out of range")))?;
+    }
+    Ok(set)
+}
+
+#[cfg(unix)]
+mod unix_priority {
+    use nix::errno::Errno;
+    use nix::libc;
+
+    pub(super) fn get(pid: u32) -> nix::Result<i32> {
+        // -1 is a nice value as well as the error return; only errno tells
+        Errno::clear();
+        // SAFETY: getpriority takes no pointers
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, pid as libc::id_t) };
+        match Errno::last() {
+            Errno::UnknownErrno => Ok(nice),
+            _ if nice != -1 => Ok(nice),
+            errno => Err(errno),
+        }
+    }
+
+    pub(super) fn set(pid: u32, nice: i32) -> nix::Result<()> {
+        // SAFETY: setpriority takes no pointers
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, pid as libc::id_t, nice) };
+        Errno::result(result).map(drop)
+    }
+}
+
+/// Nice values stand for the six Windows priority classes; realtime is
+/// never chosen for one, as it can starve the system
+#[cfg(windows)]
+mod windows_priority {
+    use super::ProcessId;
+    use crate::error::{HalError, HalResult};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, GetPriorityClass, OpenProcess, SetPriorityClass, SetProcessAffinityMask,
+        ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+        IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, PROCESS_QUERY_LIMITED_INFORMATION,
+        PROCESS_SET_INFORMATION, REALTIME_PRIORITY_CLASS,
+    };
+
+    pub(super) fn class(nice: i32) -> u32 {
+        match nice {
+            ..=-15 => HIGH_PRIORITY_CLASS,
+            -14..=-5 => ABOVE_NORMAL_PRIORITY_CLASS,
+            -4..=4 => NORMAL_PRIORITY_CLASS,
+            5..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+            _ => IDLE_PRIORITY_CLASS,
+        }
+    }
+
+    pub(super) fn nice(class: u32) -> i32 {
+        match class {
+            REALTIME_PRIORITY_CLASS => -20,
+            HIGH_PRIORITY_CLASS => -15,
+            ABOVE_NORMAL_PRIORITY_CLASS => -10,
+            BELOW_NORMAL_PRIORITY_CLASS => 10,
+            IDLE_PRIORITY_CLASS => 19,
+            _ => 0,
+        }
+    }
+
+    /// Run `f` with a handle to process `pid` opened for `access`
+    fn with_process<T>(
+        operation: &str,
+        pid: ProcessId,
+        access: u32,
+        f: impl FnOnce(HANDLE) -> T,
+    ) -> HalResult<T> {
+        if pid == 0 {
+            // SAFETY: the pseudo handle needs no closing
+            return Ok(f(unsafe { GetCurrentProcess() }));
+        }
+        // SAFETY: OpenProcess takes no pointers and returns 0 on failure
+        let handle = unsafe { OpenProcess(access, 0, pid) };
+        if handle == 0 {
+            return Err(last_error(operation, pid));
+        }
+        let result = f(handle);
+        // SAFETY: the handle is owned here and closed once
+        unsafe { CloseHandle(handle) };
+        Ok(result)
+    }
+
+    fn last_error(operation: &str, pid: ProcessId) -> HalError {
+        HalError::process_error(
+            operation,
+            Some(pid),
+            &std::io::Error::last_os_error().to_string(),
+        )
+    }
+
+    pub(super) fn get(pid: ProcessId) -> HalResult<i32> {
+        // SAFETY: the handle is open for querying
+        let class = with_process(
+            "GetPriorityClass",
+            pid,
+            PROCESS_QUERY_LIMITED_INFORMATION,
+            |h| unsafe { GetPriorityClass(h) },
+        )?;
+        if class == 0 {
+            return Err(last_error("GetPriorityClass", pid));
+        }
+        Ok(nice(class))
+    }
+
+    pub(super) fn set(pid: ProcessId, nice: i32) -> HalResult<()> {
+        // SAFETY: the handle is open for setting information
+        let ok = with_process(
+            "SetPriorityClass",
+            pid,
+            PROCESS_SET_INFORMATION,
+            |h| unsafe { SetPriorityClass(h, class(nice)) },
+        )?;
+        if ok == 0 {
+            return Err(last_error("SetPriorityClass", pid));
+        }
+        Ok(())
+    }
+
+    pub(super) fn affinity_mask(cpus: &[usize]) -> HalResult<usize> {
+        let mut mask = 0usize;
+        for &cpu in cpus {
+            if cpu >= usize::BITS as usize {
+                return Err(HalError::invalid(&format!("CPU {cpu} is out of range")));
+            }
+            mask |= 1 << cpu;
+        }
+        Ok(mask)
+    }
+
+    pub(super) fn set_affinity(pid: ProcessId, cpus: &[usize]) -> HalResult<()> {
+        let mask = affinity_mask(cpus)?;
+        // SAFETY: the handle is open for setting information
+        let ok = with_process(
+            "SetProcessAffinityMask",
+            pid,
+            PROCESS_SET_INFORMATION,
+            |h| unsafe { SetProcessAffinityMask(h, mask) },
+        )?;
+        if ok == 0 {
+            return Err(last_error("SetProcessAffinityMask", pid));
+        }
+        Ok(())
+    }
+}
+
+/// What [`rebind_fd`] points one of the shell's own descriptors at
+#[derive(Debug)]
+pub enum FdTarget {
+    /// An opened file, as for `exec 3<file` or `exec >log`
+    File(std::fs::File),
+    /// Whatever another descriptor refers to, as for `exec 2>&1`
+    Duplicate(u32),
+    /// Nothing, as for `exec 3>&-`
+    Close,
+}
+
+/// Point file descriptor `fd` of the shell process at `target` for the rest
+/// of the session. Programs started afterwards inherit the new descriptor.
+#[cfg(unix)]
+pub fn rebind_fd(fd: u32, target: FdTarget) -> HalResult<()> {
+    use nix::errno::Errno;
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    use nix::unistd::{close, dup2};
+    use std::os::fd::IntoRawFd;
+
+    let fd = fd as i32;
+    let result = match target {
+        FdTarget::File(file) => {
+            let raw = file.into_raw_fd();
+            if raw == fd {
+                // Opened straight into the free slot; children must still see it
+                fcntl(raw, FcntlArg::F_SETFD(FdFlag::empty())).map(drop)
+            } else {
+                let result = dup2(raw, fd).map(drop);
+                let _ = close(raw);
+                result
+            }
+        }
+        FdTarget::Duplicate(from) => dup2(from as i32, fd).map(drop),
+        // Closing a descriptor that is not open is not an error
+        FdTarget::Close => match close(fd) {
+            Err(Errno::EBADF) => Ok(()),
+            other => other,
+        },
+    };
+    result.map_err(|e| HalError::io_error("rebind_fd", None, e.into()))
+}
+
+/// Point standard input, output or error of the shell process at `target`.
+/// Windows has no numbered descriptors beyond the three standard handles.
+#[cfg(windows)]
+pub fn rebind_fd(fd: u32, target: FdTarget) -> HalResult<()> {
+    use std::os::windows::io::IntoRawHandle;
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::System::Console::{
+        GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    let std_handle = |fd: u32| match fd {
+        0 => Ok(STD_INPUT_HANDLE),
+        1 => Ok(STD_OUTPUT_HANDLE),
+        2 => Ok(STD_ERROR_HANDLE),
+        _ => Err(HalError::unsupported(&format!(
+            "file descriptor {fd} is not available on Windows"
+        ))),
+    };
+    let slot = std_handle(fd)?;
+    let handle = match target {
+        FdTarget::File(file) => file.into_raw_handle() as _,
+        // SAFETY: querying a standard handle has no preconditions
+        FdTarget::Duplicate(from) => unsafe { GetStdHandle(std_handle(from)?) },
+        FdTarget::Close => INVALID_HANDLE_VALUE,
+    };
+    // SAFETY: `handle` is owned by the process from here on
+    if unsafe { SetStdHandle(slot, handle) } == 0 {
+        return Err(HalError::io_error(
+            "rebind_fd",
+            None,
+            std::io::Error::last_os_error(),
+        ));
+    }
+    Ok(())
+}
+
+/// Wait for the next state change of a child process, including stops and
+/// continues, unlike [`Child::wait`] which only returns once it exits
+#[cfg(unix)]
+pub fn wait_for_state_change(pid: ProcessId) -> HalResult<ChildState> {
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+    use nix::unistd::Pid;
+
+    loop {
+        let status = waitpid(
+            Pid::from_raw(pid as i32),
+            Some(WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED),
+        )
+        .map_err(|e| HalError::process_error("waitpid", Some(pid), &e.to_string()))?;
+        match status {
+            WaitStatus::Exited(_, code) => return Ok(ChildState::Exited(code)),
+            WaitStatus::Signaled(_, sig, _) => return Ok(ChildState::Signaled(sig as i32)),
+            WaitStatus::Stopped(_, sig) => return Ok(ChildState::Stopped(sig as i32)),
+            WaitStatus::Continued(_) => return Ok(ChildState::Continued),
+            // Ptrace events and spurious wakeups are not job state changes
+            _ => continue,
+        }
+    }
+}
+
+/// Resume every process in a stopped process group
+#[cfg(unix)]
+pub fn resume_process_group(pgid: ProcessGroupId) -> HalResult<()> {
+    use nix::sys::signal::{killpg, Signal};
+    use nix::unistd::Pid;
+
+    killpg(Pid::from_raw(pgid as i32), Signal::SIGCONT)
+        .map_err(|e| HalError::process_error("killpg", Some(pgid), &e.to_string()))
+}
+
+/// Ownership of the controlling terminal for an interactive shell.
+///
+/// On Unix the shell runs in its own process group and hands the terminal's
+/// foreground group to each foreground job with `tcsetpgrp`, so the job
+/// receives SIGINT and SIGTSTP from the keyboard, then takes it back once the
+/// job exits or stops. On Windows the console input mode is saved and
+/// restored around each job instead.
+#[derive(Debug)]
+pub struct TerminalControl {
+    #[cfg(unix)]
+    shell_pgid: ProcessGroupId,
+    #[cfg(windows)]
+    shell_mode: u32,
+}
+
+#[cfg(unix)]
+impl TerminalControl {
+    const TTY_FD: i32 = 0;
+
+    /// Take control of the terminal on stdin. Returns `None` when stdin is
+    /// not a terminal, in which case job control stays off.
+    pub fn acquire() -> HalResult<Option<Self>> {
+        use nix::sys::signal::{kill, signal, SigHandler, Signal};
+        use nix::unistd::{getpgrp, getpid, isatty, setpgid, tcgetpgrp};
+
+        if !isatty(Self::TTY_FD).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        // Started in the background: wait until the user brings us forward
+        loop {
+            let foreground = tcgetpgrp(Self::TTY_FD)
+                .map_err(|e| HalError::process_error("tcgetpgrp", None, &e.to_string()))?;
+            if foreground == getpgrp() {
+                break;
+            }
+            let _ = kill(getpgrp(), Signal::SIGTTIN);
+        }
+
+        // SAFETY: switching to SIG_IGN installs no handler code
+        unsafe {
+            for sig in [
+                Signal::SIGTSTP,
+                Signal::SIGTTIN,
+                Signal::SIGTTOU,
+                Signal::SIGQUIT,
+            ] {
+                signal(sig, SigHandler::SigIgn)
+                    .map_err(|e| HalError::process_error("signal", None, &e.to_string()))?;
+            }
+        }
+
+        let pid = getpid();
+        // Fails with EPERM when we already lead a session; that is fine
+        let _ = setpgid(pid, pid);
+        let control = Self {
+            shell_pgid: getpgrp().as_raw() as ProcessGroupId,
+        };
+        control.reclaim()?;
+        Ok(Some(control))
+    }
+
+    /// Process group of the shell itself
+    pub fn shell_pgid(&self) -> ProcessGroupId {
+        self.shell_pgid
+    }
+
+    /// Make `pgid` the terminal's foreground process group
+    pub fn give_to(&self, pgid: ProcessGroupId) -> HalResult<()> {
+        use nix::unistd::{tcsetpgrp, Pid};
+
+        tcsetpgrp(Self::TTY_FD, Pid::from_raw(pgid as i32))
+            .map_err(|e| HalError::process_error("tcsetpgrp", Some(pgid), &e.to_string()))
+    }
+
+    /// Make the shell the terminal's foreground process group again
+    pub fn reclaim(&self) -> HalResult<()> {
+        // SIGTTOU is ignored in the shell, so this works from the background
+        self.give_to(self.shell_pgid)
+    }
+}
+
+#[cfg(windows)]
+impl TerminalControl {
+    fn console_input() -> windows_sys::Win32::Foundation::HANDLE {
+        use windows_sys::Win32::System::Console::{GetStdHandle, STD_INPUT_HANDLE};
+        // SAFETY: GetStdHandle has no preconditions
+        unsafe { GetStdHandle(STD_INPUT_HANDLE) }
+    }
+
+    /// Remember the shell's console input mode. Returns `None` when stdin is
+    /// not a console.
+    pub fn acquire() -> HalResult<Option<Self>> {
+        use windows_sys::Win32::System::Console::GetConsoleMode;
+
+        let mut mode = 0;
+        // SAFETY: `mode` is a valid out pointer for the duration of the call
+        if unsafe { GetConsoleMode(Self::console_input(), &mut mode) } == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self { shell_mode: mode }))
+    }
+
+    /// Give the console to a job: Ctrl-C is delivered as a signal and input
+    /// is line-buffered and echoed, as console programs expect
+    pub fn give_to(&self, _pgid: ProcessGroupId) -> HalResult<()> {
+        use windows_sys::Win32::System::Console::{
+            ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT, ENABLE_PROCESSED_INPUT,
+        };
+
+        self.set_mode(ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT)
+    }
+
+    /// Restore the console mode the shell's line editor runs with
+    pub fn reclaim(&self) -> HalResult<()> {
+        self.set_mode(self.shell_mode)
+    }
+
+    fn set_mode(&self, mode: u32) -> HalResult<()> {
+        use windows_sys::Win32::System::Console::SetConsoleMode;
+
+        // SAFETY: the handle comes from GetStdHandle and stays open
+        if unsafe { SetConsoleMode(Self::console_input(), mode) } == 0 {
+            return Err(HalError::process_error(
+                "SetConsoleMode",
+                None,
+                &std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A Windows Job Object grouping the processes of one shell job, so the
+/// whole job can be terminated together like a Unix process group
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct JobObject {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+#[cfg(windows)]
+impl JobObject {
+    /// Create an anonymous job object
+    pub fn new() -> HalResult<Self> {
+        use windows_sys::Win32::System::JobObjects::CreateJobObjectW;
+
+        // SAFETY: null attributes and name create an unnamed default job
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle == 0 {
+            return Err(HalError::process_error(
+                "CreateJobObjectW",
+                None,
+                &std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        Ok(Self { handle })
+    }
+
+    /// Add a spawned child to the job
+    pub fn assign(&self, child: &Child) -> HalResult<()> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+
+        // SAFETY: both handles are valid for the duration of the call
+        if unsafe { AssignProcessToJobObject(self.handle, child.as_raw_handle() as _) } == 0 {
+            return Err(HalError::process_error(
+                "AssignProcessToJobObject",
+                Some(child.id()),
+                &std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Apply the limits set through [`crate::resource::set_limit`] that a
+    /// job object can enforce
+    pub fn apply_resource_limits(&self) -> HalResult<()> {
+        use crate::resource::{job_limits, Resource};
+
+        self.limit(
+            job_limits::soft(Resource::CpuTime).map(Duration::from_secs),
+            job_limits::soft(Resource::VirtualMemory),
+            job_limits::soft(Resource::Processes),
+        )
+    }
+
+    /// Limit the CPU time and memory of each process in the job, and the
+    /// number of processes alive at once; `None` leaves a limit off
+    pub fn limit(
+        &self,
+        cpu_time: Option<Duration>,
+        memory: Option<u64>,
+        processes: Option<u64>,
+    ) -> HalResult<()> {
+        use windows_sys::Win32::System::JobObjects::{
+            JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_ACTIVE_PROCESS,
+            JOB_OBJECT_LIMIT_PROCESS_MEMORY, JOB_OBJECT_LIMIT_PROCESS_TIME,
+        };
+
+        // SAFETY: the structure is plain data for which all zeros is valid
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        let basic = &mut info.BasicLimitInformation;
+        if let Some(cpu_time) = cpu_time {
+            // In 100-nanosecond ticks
+            basic.PerProcessUserTimeLimit =
+                (cpu_time.as_nanos() / 100).min(i64::MAX as u128) as i64;
+            basic.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_TIME;
+        }
+        if let Some(count) = processes {
+            basic.ActiveProcessLimit = count.min(u32::MAX as u64) as u32;
+            basic.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+        }
+        if let Some(bytes) = memory {
+            info.ProcessMemoryLimit = bytes.min(usize::MAX as u64) as usize;
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+        }
+        if info.BasicLimitInformation.LimitFlags == 0 {
+            return Ok(());
+        }
+
+        // SAFETY: the pointer and size describe `info`, which outlives the call
+        let ok = unsafe {
+            SetInformationJobObject(
+                self.handle,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const std::ffi::c_void,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        };
+        if ok == 0 {
+            return Err(HalError::process_error(
+                "SetInformationJobObject",
+                None,
+                &std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Terminate every process in the job
+    pub fn terminate(&self, exit_code: u32) -> HalResult<()> {
+        use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+
+        // SAFETY: the handle is owned by `self`
+        if unsafe { TerminateJobObject(self.handle, exit_code) } == 0 {
+            return Err(HalError::process_error(
+                "TerminateJobObject",
+                None,
+                &std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        // SAFETY: the handle is owned by `self` and closed exactly once
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// One process as read by [`ProcessSnapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessEntry {
+    pub pid: ProcessId,
+    /// 0 for processes without a parent
+    pub ppid: ProcessId,
+    /// The owner's name, or their numeric ID when it has none
+    pub user: String,
+    pub state: ProcessStatus,
+    /// Share of one CPU, averaged over the process's life in a captured
+    /// snapshot and over the interval in a refreshed one
+    pub cpu_percent: f64,
+    /// User and system CPU time used so far
+    pub cpu_time: Duration,
+    /// Resident memory in bytes
+    pub rss: u64,
+    /// Virtual memory in bytes
+    pub vsz: u64,
+    /// The executable's name
+    pub name: String,
+    /// The arguments joined by spaces, or the name in brackets when they
+    /// cannot be read
+    pub command_line: String,
+    /// The controlling terminal, like `pts/0`
+    pub tty: Option<String>,
+    pub start_time: Option<SystemTime>,
+    pub nice: i32,
+    /// 0 where the platform does not say
+    pub threads: u32,
+}
+
+/// The processes running at one moment, read from `/proc` on Linux, from
+/// `ps` on macOS and through the Toolhelp and process query APIs on
+/// Windows
+#[derive(Debug, Clone)]
+pub struct ProcessSnapshot {
+    /// In PID order
+    processes: Vec<ProcessEntry>,
+    total_memory: u64,
+    taken: Instant,
+}
+
+impl ProcessSnapshot {
+    /// Read every visible process, with CPU use averaged over each one's
+    /// life as `ps` shows it
+    pub fn capture() -> HalResult<Self> {
+        let mut processes = read_processes()?;
+        processes.sort_by_key(|process| process.pid);
+        let now = SystemTime::now();
+        for process in &mut processes {
+            let life = process
+                .start_time
+                .and_then(|start| now.duration_since(start).ok());
+            process.cpu_percent = life.map_or(0.0, |life| cpu_percent(process.cpu_time, life));
+        }
+        let total_memory = crate::memory::MemoryManager::new()
+            .and_then(|memory| memory.memory_info())
+            .map_or(0, |info| info.total_physical);
+        Ok(Self {
+            processes,
+            total_memory,
+            taken: Instant::now(),
+        })
+    }
+
+    /// Read the processes again, with CPU use measured since this snapshot
+    /// as `top` shows it
+    pub fn refresh(&self) -> HalResult<Self> {
+        let mut next = Self::capture()?;
+        let elapsed = next.taken.duration_since(self.taken);
+        for process in &mut next.processes {
+            // A PID reused since then belongs to another process
+            let before = self
+                .get(process.pid)
+                .filter(|old| old.start_time == process.start_time)
+                .map_or(Duration::ZERO, |old| old.cpu_time);
+            process.cpu_percent = cpu_percent(process.cpu_time.saturating_sub(before), elapsed);
+        }
+        Ok(next)
+    }
+
+    /// The processes in PID order
+    pub fn processes(&self) -> &[ProcessEntry] {
+        &self.processes
+    }
+
+    pub fn into_processes(self) -> Vec<ProcessEntry> {
+        self.processes
+    }
+
+    pub fn get(&self, pid: ProcessId) -> Option<&ProcessEntry> {
+        self.processes
+            .binary_search_by_key(&pid, |process| process.pid)
+            .ok()
+            .map(|index| &self.processes[index])
+    }
+
+    /// Physical memory in bytes, or 0 when it could not be read
+    pub fn total_memory(&self) -> u64 {
+        self.total_memory
+    }
+
+    /// The resident memory of `process` as a percentage of physical memory
+    pub fn memory_percent(&self, process: &ProcessEntry) -> f64 {
+        if self.total_memory == 0 {
+            0.0
+        } else {
+            process.rss as f64 * 100.0 / self.total_memory as f64
+        }
+    }
+}
+
+fn cpu_percent(used: Duration, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        used.as_secs_f64() * 100.0 / elapsed.as_secs_f64()
+    }
+}
+
+/// Clock ticks per second in `/proc/PID/stat`, which the kernel fixes at
+/// 100 for user space
+#[cfg(target_os = "linux")]
+const CLOCK_TICKS: u64 = 100;
+
+#[cfg(target_os = "linux")]
+fn read_processes() -> HalResult<Vec<ProcessEntry>> {
+    let boot_time = std::fs::read_to_string("/proc/stat").ok().and_then(|stat| {
+        stat.lines()
+            .find_map(|line| line.strip_prefix("btime ")?.trim().parse::<u64>().ok())
+    });
+    let entries =
+        std::fs::read_dir("/proc").map_err(|e| HalError::io_error("read_dir", Some("/proc"), e))?;
+    let mut users = HashMap::new();
+    let mut processes = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        // A process may exit while it is read
+        if let Some(process) = read_linux_process(pid, boot_time, &mut users) {
+            processes.push(process);
+        }
+    }
+    Ok(processes)
+}
+
+#[cfg(target_os = "linux")]
+fn read_linux_process(
+    pid: ProcessId,
+    boot_time: Option<u64>,
+    users: &mut HashMap<u32, String>,
+) -> Option<ProcessEntry> {
+    let dir = Path::new("/proc").join(pid.to_string());
+    let stat = std::fs::read_to_string(dir.join("stat")).ok()?;
+    let mut process = parse_stat(pid, &stat, boot_time)?;
+
+    let status = std::fs::read_to_string(dir.join("status")).unwrap_or_default();
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+    };
+    let bytes = |name: &str| {
+        field(name)
+            .and_then(|value| value.trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map_or(0, |kib| kib * 1024)
+    };
+    process.rss = bytes("VmRSS:");
+    process.vsz = bytes("VmSize:");
+    let uid = field("Uid:").and_then(|ids| ids.split_whitespace().next()?.parse::<u32>().ok());
+    if let Some(uid) = uid {
+        process.user = users.entry(uid).or_insert_with(|| user_name(uid)).clone();
+    }
+
+    let cmdline = std::fs::read(dir.join("cmdline")).unwrap_or_default();
+    let args: Vec<String> = cmdline
+        .split(|&byte| byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if !args.is_empty() {
+        process.command_line = args.join(" ");
+    }
+    Some(process)
+}
+
+/// Read `/proc/PID/stat`, whose second field is the name in parentheses,
+/// which may itself hold spaces and parentheses
+#[cfg(target_os = "linux")]
+fn parse_stat(pid: ProcessId, stat: &str, boot_time: Option<u64>) -> Option<ProcessEntry> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    let number = |index: usize| {
+        fields
+            .get(index)
+            .and_then(|field| field.parse::<i64>().ok())
+            .unwrap_or(0)
+    };
+    let ticks = |count: i64| Duration::from_millis(count.max(0) as u64 * 1000 / CLOCK_TICKS);
+    let state = match fields.first()?.chars().next()? {
+        'R' => ProcessStatus::Running,
+        'S' | 'D' | 'I' => ProcessStatus::Sleeping,
+        'T' | 't' => ProcessStatus::Stopped,
+        'Z' => ProcessStatus::Zombie,
+        'X' | 'x' => ProcessStatus::Exited(0),
+        _ => ProcessStatus::Unknown,
+    };
+    Some(ProcessEntry {
+        pid,
+        ppid: number(1) as ProcessId,
+        user: String::new(),
+        state,
+        cpu_percent: 0.0,
+        cpu_time: ticks(number(11) + number(12)),
+        rss: 0,
+        vsz: 0,
+        command_line: format!("[{name}]"),
+        name,
+        tty: tty_name(number(4)),
+        start_time: boot_time
+            .map(|boot| std::time::UNIX_EPOCH + Duration::from_secs(boot) + ticks(number(19))),
+        nice: number(16) as i32,
+        threads: number(17) as u32,
+    })
+}
+
+/// The name of a terminal from its device number in `/proc/PID/stat`
+#[cfg(target_os = "linux")]
+fn tty_name(device: i64) -> Option<String> {
+    let major = (device >> 8) & 0xfff;
+    let minor = (device & 0xff) | ((device >> 12) & 0xfff00);
+    match major {
+        4 if minor < 64 => Some(format!("tty{minor}")),
+        4 => Some(format!("ttyS{}", minor - 64)),
+        136..=143 => Some(format!("pts/{}", (major - 136) * 256 + minor)),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn user_name(uid: u32) -> String {
+    use nix::unistd::{Uid, User};
+    match User::from_uid(Uid::from_raw(uid)) {
+        Ok(Some(user)) => user.name,
+        _ => uid.to_string(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn read_processes() -> HalResult<Vec<ProcessEntry>> {
+    // Without libc there is no proc_pidinfo, but ps(1) reads the same data
+    // and its output format has not changed in decades
+    let output = Command::new("/bin/ps")
+        .args([
+            "-axww",
+            "-o",
+            "pid=,ppid=,user=,state=,nice=,rss=,vsz=,time=,etime=,tty=,args=",
+        ])
+        .output()
+        .map_err(|e| HalError::io_error("spawn", Some("/bin/ps"), e))?;
+    if !output.status.success() {
+        return Err(HalError::process_error(
+            "ps",
+            None,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+    let now = SystemTime::now();
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| parse_ps_line(line, now))
+        .collect())
+}
+
+/// Read one line of the `ps -o` listing in [`read_processes`]
+#[cfg(target_os = "macos")]
+fn parse_ps_line(line: &str, now: SystemTime) -> Option<ProcessEntry> {
+    let mut fields = line.split_whitespace();
+    let pid = fields.next()?.parse().ok()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let user = fields.next()?.to_string();
+    let state = match fields.next()?.chars().next()? {
+        'R' => ProcessStatus::Running,
+        'S' | 'I' | 'U' => ProcessStatus::Sleeping,
+        'T' => ProcessStatus::Stopped,
+        'Z' => ProcessStatus::Zombie,
+        _ => ProcessStatus::Unknown,
+    };
+    let nice = fields.next()?.parse().unwrap_or(0);
+    let rss = fields.next()?.parse::<u64>().ok()? * 1024;
+    let vsz = fields.next()?.parse::<u64>().ok()? * 1024;
+    let cpu_time = ps_duration(fields.next()?)?;
+    let elapsed = ps_duration(fields.next()?)?;
+    let tty = match fields.next()? {
+        "??" => None,
+        tty => Some(tty.to_string()),
+    };
+    let command_line = fields.collect::<Vec<_>>().join(" ");
+    let program = command_line.split_whitespace().next().unwrap_or_default();
+    let name = program.rsplit('/').next().unwrap_or(program).to_string();
+    Some(ProcessEntry {
+        pid,
+        ppid,
+        user,
+        state,
+        cpu_percent: 0.0,
+        cpu_time,
+        rss,
+        vsz,
+        name,
+        command_line,
+        tty,
+        start_time: now.checked_sub(elapsed),
+        nice,
+        threads: 0,
+    })
+}
+
+/// Read a `ps` duration, `[[DD-]HH:]MM:SS[.CC]`
+#[cfg(target_os = "macos")]
+fn ps_duration(text: &str) -> Option<Duration> {
+    let (days, clock) = match text.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, text),
+    };
+    let mut seconds = 0.0;
+    for part in clock.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(Duration::from_secs(days * 86_400) + Duration::from_secs_f64(seconds))
+}
+
+#[cfg(windows)]
+fn read_processes() -> HalResult<Vec<ProcessEntry>> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+
+    // SAFETY: a process snapshot takes no pointers
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Err(HalError::process_error(
+            "CreateToolhelp32Snapshot",
+            None,
+            &std::io::Error::last_os_error().to_string(),
+        ));
+    }
+    let mut processes = Vec::new();
+    // SAFETY: PROCESSENTRY32W is plain data, for which zeroes are valid
+    let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+    // SAFETY: the snapshot is open and `entry` has its size set
+    let mut more = unsafe { Process32FirstW(snapshot, &mut entry) } != 0;
+    while more {
+        let end = entry
+            .szExeFile
+            .iter()
+            .position(|&unit| unit == 0)
+            .unwrap_or(entry.szExeFile.len());
+        let name = String::from_utf16_lossy(&entry.szExeFile[..end]);
+        let mut process = ProcessEntry {
+            pid: entry.th32ProcessID,
+            ppid: entry.th32ParentProcessID,
+            user: String::new(),
+            state: ProcessStatus::Running,
+            cpu_percent: 0.0,
+            cpu_time: Duration::ZERO,
+            rss: 0,
+            vsz: 0,
+            command_line: name.clone(),
+            name,
+            tty: None,
+            start_time: None,
+            nice: 0,
+            threads: entry.cntThreads,
+        };
+        query_windows_process(&mut process);
+        processes.push(process);
+        // SAFETY: as for Process32FirstW
+        more = unsafe { Process32NextW(snapshot, &mut entry) } != 0;
+    }
+    // SAFETY: the snapshot handle is owned here and closed once
+    unsafe { CloseHandle(snapshot) };
+    Ok(processes)
+}
+
+/// Fill in what needs a handle to the process, which system processes and
+/// those of other users may not give
+#[cfg(windows)]
+fn query_windows_process(process: &mut ProcessEntry) {
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows_sys::Win32::System::ProcessStatus::{
+        K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetPriorityClass, GetProcessTimes, OpenProcess, QueryFullProcessImageNameW,
+        PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: OpenProcess takes no pointers and returns 0 on failure
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process.pid) };
+    if handle == 0 {
+        return;
+    }
+
+    let zero = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+    // SAFETY: the handle is open and each pointer is to a local FILETIME
+    if unsafe { GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user) } != 0 {
+        process.cpu_time = filetime(kernel) + filetime(user);
+        // FILETIMEs count from 1601, 11644473600 seconds before 1970
+        process.start_time = filetime(created)
+            .checked_sub(Duration::from_secs(11_644_473_600))
+            .map(|since_epoch| std::time::UNIX_EPOCH + since_epoch);
+    }
+
+    // SAFETY: PROCESS_MEMORY_COUNTERS is plain data, for which zeroes are
+    // valid
+    let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    counters.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: `counters` is as large as `cb` says
+    if unsafe { K32GetProcessMemoryInfo(handle, &mut counters, counters.cb) } != 0 {
+        process.rss = counters.WorkingSetSize as u64;
+        process.vsz = counters.PagefileUsage as u64;
+    }
+
+    let mut path = [0u16; 1024];
+    let mut len = path.len() as u32;
+    // SAFETY: `len` holds the buffer's length in UTF-16 units
+    let named = unsafe {
+        QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len)
+    };
+    if named != 0 {
+        process.command_line = String::from_utf16_lossy(&path[..len as usize]);
+    }
+
+    if let Some(user) = windows_process_user(handle) {
+        process.user = user;
+    }
+    // SAFETY: the handle is open for querying
+    let class = unsafe { GetPriorityClass(handle) };
+    if class != 0 {
+        process.nice = windows_priority::nice(class);
+    }
+    // SAFETY: the process handle is owned here and closed once
+    unsafe { CloseHandle(handle) };
+}
+
+/// A duration from a FILETIME, which counts 100ns intervals
+#[cfg(windows)]
+fn filetime(time: windows_sys::Win32::Foundation::FILETIME) -> Duration {
+    let intervals = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+    Duration::from_nanos(intervals.saturating_mul(100))
+}
+
+/// The account name owning the process behind `handle`
+#[cfg(windows)]
+fn windows_process_user(handle: windows_sys::Win32::Foundation::HANDLE) -> Option<String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, LookupAccountSidW, TokenUser, SID_NAME_USE, TOKEN_QUERY, TOKEN_USER,
+    };
+    use windows_sys::Win32::System::Threading::OpenProcessToken;
+
+    let mut token = 0;
+    // SAFETY: `token` receives the token handle
+    if unsafe { OpenProcessToken(handle, TOKEN_QUERY, &mut token) } == 0 {
+        return None;
+    }
+    // A TOKEN_USER followed by the SID it points to; u64s keep it aligned
+    let mut buffer = [0u64; 64];
+    let mut needed = 0u32;
+    // SAFETY: the buffer is as large as the length passed
+    let read = unsafe {
+        GetTokenInformation(
+            token,
+            TokenUser,
+            buffer.as_mut_ptr().cast(),
+            std::mem::size_of_val(&buffer) as u32,
+            &mut needed,
+        )
+    };
+    // SAFETY: the token handle is owned here and closed once
+    unsafe { CloseHandle(token) };
+    if read == 0 {
+        return None;
+    }
+    // SAFETY: GetTokenInformation filled the buffer with a TOKEN_USER
+    let sid = unsafe { (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid };
+
+    let mut name = [0u16; 256];
+    let mut name_len = name.len() as u32;
+    let mut domain = [0u16; 256];
+    let mut domain_len = domain.len() as u32;
+    let mut kind: SID_NAME_USE = 0;
+    // SAFETY: both lengths hold their buffers' sizes in UTF-16 units
+    let found = unsafe {
+        LookupAccountSidW(
+            std::ptr::null(),
+            sid,
+            name.as_mut_ptr(),
+            &mut name_len,
+            domain.as_mut_ptr(),
+            &mut domain_len,
+            &mut kind,
+        )
+    };
+    (found != 0).then(|| String::from_utf16_lossy(&name[..name_len as usize]))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn read_processes() -> HalResult<Vec<ProcessEntry>> {
+    Err(HalError::unsupported(
+        "Process listing is not supported on this platform",
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -767,6 +2074,93 @@ mod tests {
         assert_eq!(config.args.len(), 2);
         assert_eq!(config.env.get("TEST"), Some(&"value".to_string()));
     }
+
+    // The child is reaped by the last wait_for_state_change, which sees it die
+    #[allow(clippy::zombie_processes)]
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_for_state_change_sees_stops() {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        prepare_job_command(&mut command, 0);
+        let child = command.spawn().unwrap();
+        let pid = child.id();
+        let target = Pid::from_raw(pid as i32);
+
+        kill(target, Signal::SIGSTOP).unwrap();
+        assert_eq!(
+            wait_for_state_change(pid).unwrap(),
+            ChildState::Stopped(Signal::SIGSTOP as i32)
+        );
+        resume_process_group(pid).unwrap();
+        assert_eq!(wait_for_state_change(pid).unwrap(), ChildState::Continued);
+        kill(target, Signal::SIGKILL).unwrap();
+        assert_eq!(
+            wait_for_state_change(pid).unwrap(),
+            ChildState::Signaled(Signal::SIGKILL as i32)
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    #[test]
+    fn test_snapshot_lists_this_process() {
+        let snapshot = ProcessSnapshot::capture().unwrap();
+        let me = snapshot
+            .get(std::process::id())
+            .expect("own process listed");
+        assert!(me.rss > 0);
+        assert!(!me.name.is_empty());
+        #[cfg(unix)]
+        assert_eq!(me.ppid, std::os::unix::process::parent_id());
+
+        let next = snapshot.refresh().unwrap();
+        let me = next.get(std::process::id()).unwrap();
+        assert!(me.cpu_percent >= 0.0);
+        assert!(next.processes().windows(2).all(|w| w[0].pid < w[1].pid));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_stat() {
+        let stat = "42 (my (odd) prog) S 1 42 42 34816 42 4194304 100 0 0 0 250 50 0 0 20 0 3 0 \
+                    1000 1234567 89 18446744073709551615";
+        let process = parse_stat(42, stat, Some(1_700_000_000)).unwrap();
+        assert_eq!(process.name, "my (odd) prog");
+        assert_eq!(process.command_line, "[my (odd) prog]");
+        assert_eq!(process.ppid, 1);
+        assert_eq!(process.state, ProcessStatus::Sleeping);
+        assert_eq!(process.cpu_time, Duration::from_secs(3));
+        assert_eq!(process.tty.as_deref(), Some("pts/0"));
+        assert_eq!(process.threads, 3);
+        assert_eq!(
+            process.start_time,
+            Some(std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_010))
+        );
+        assert_eq!(tty_name(0), None);
+        assert_eq!(tty_name(0x401).as_deref(), Some("tty1"));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_parse_ps_line() {
+        let now = SystemTime::now();
+        let line = "  501   1 alice  S+  0  2048  40960  1:02.50  1-00:00:10 ttys001 /bin/zsh -l";
+        let process = parse_ps_line(line, now).unwrap();
+        assert_eq!((process.pid, process.ppid), (501, 1));
+        assert_eq!(process.user, "alice");
+        assert_eq!(process.rss, 2048 * 1024);
+        assert_eq!(process.cpu_time, Duration::from_millis(62_500));
+        assert_eq!(process.name, "zsh");
+        assert_eq!(process.command_line, "/bin/zsh -l");
+        assert_eq!(process.tty.as_deref(), Some("ttys001"));
+        assert_eq!(
+            process.start_time,
+            now.checked_sub(Duration::from_secs(86_410))
+        );
+    }
 }
 
 // Include comprehensive ProcessHandle tests
@@ -0,0 +1,719 @@
+//! Pseudo-terminals for interactive programs
+//!
+//! A [`Pty`] is a terminal the shell makes up: programs started on it with
+//! [`Pty::spawn`] see a real TTY on their standard streams, so editors,
+//! remote shells and REPLs work even when the shell's own streams are not a
+//! terminal it can hand over. [`Pty::relay`] copies the shell's input to the
+//! program and its output back, and passes on changes to the size of the
+//! shell's terminal as they happen.
+//!
+//! Unix uses `openpty`; Windows uses a pseudo console (ConPTY).
+
+use crate::error::HalResult;
+use crate::terminal::{ResizeWatch, TerminalSize};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+/// The size a pseudo-terminal gets when nothing says otherwise
+pub const DEFAULT_SIZE: TerminalSize = TerminalSize {
+    columns: 80,
+    rows: 24,
+};
+
+/// How often [`Pty::relay`] looks for a changed terminal size while the
+/// program is quiet
+const TICK: Duration = Duration::from_millis(100);
+
+/// A pseudo-terminal one program runs on
+#[derive(Debug)]
+pub struct Pty {
+    inner: platform::Pty,
+}
+
+/// A program started on a [`Pty`]
+#[derive(Debug)]
+pub struct PtyChild {
+    inner: platform::Child,
+}
+
+impl Pty {
+    /// Open a pseudo-terminal of the given size
+    pub fn open(size: TerminalSize) -> HalResult<Self> {
+        Ok(Pty {
+            inner: platform::Pty::open(size)?,
+        })
+    }
+
+    /// Tell the pseudo-terminal, and so the program on it, its new size
+    pub fn resize(&self, size: TerminalSize) -> HalResult<()> {
+        self.inner.resize(size)
+    }
+
+    /// Start `command` with the pseudo-terminal as its standard input,
+    /// output and error, and as its controlling terminal. Only one program
+    /// can be started on each pseudo-terminal.
+    pub fn spawn(&mut self, command: &mut Command) -> std::io::Result<PtyChild> {
+        Ok(PtyChild {
+            inner: self.inner.spawn(command)?,
+        })
+    }
+
+    /// Copy the shell's standard input to the program and the program's
+    /// output to the shell's standard output until the program exits,
+    /// returning its status. A shell input that is a terminal is put in raw
+    /// mode meanwhile, so keys like Ctrl-C reach the program as typed.
+    pub fn relay(self, child: &mut PtyChild) -> HalResult<ExitStatus> {
+        self.inner.relay(&mut child.inner, ResizeWatch::new())
+    }
+}
+
+impl PtyChild {
+    /// The program's process id
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    /// The program's status if it has exited
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        self.inner.try_wait()
+    }
+
+    /// Wait for the program to exit
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.inner.wait()
+    }
+
+    /// Kill the program
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.inner.kill()
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::TICK;
+    use crate::error::{HalError, HalResult};
+    use crate::terminal::{ResizeWatch, TerminalSize};
+    use nix::errno::Errno;
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    use nix::libc;
+    use nix::poll::{poll, PollFd, PollFlags};
+    use nix::pty::{openpty, OpenptyResult, Winsize};
+    use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg, Termios};
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, ExitStatus, Stdio};
+
+    pub(super) type Child = std::process::Child;
+
+    #[derive(Debug)]
+    pub(super) struct Pty {
+        master: File,
+        /// Given to the program when it starts
+        slave: Option<OwnedFd>,
+    }
+
+    fn winsize(size: TerminalSize) -> Winsize {
+        Winsize {
+            ws_row: size.rows,
+            ws_col: size.columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+
+    fn failed(operation: &str, errno: Errno) -> HalError {
+        HalError::process_error(operation, None, errno.desc())
+    }
+
+    impl Pty {
+        pub(super) fn open(size: TerminalSize) -> HalResult<Self> {
+            let OpenptyResult { master, slave } =
+                openpty(&winsize(size), None).map_err(|e| failed("openpty", e))?;
+            // Neither end may leak into other programs the shell starts
+            for fd in [&master, &slave] {
+                fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+                    .map_err(|e| failed("fcntl", e))?;
+            }
+            Ok(Pty {
+                master: master.into(),
+                slave: Some(slave),
+            })
+        }
+
+        pub(super) fn resize(&self, size: TerminalSize) -> HalResult<()> {
+            let winsize = winsize(size);
+            // SAFETY: TIOCSWINSZ only reads the winsize it is given
+            if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } != 0 {
+                return Err(failed("ioctl(TIOCSWINSZ)", Errno::last()));
+            }
+            Ok(())
+        }
+
+        pub(super) fn spawn(&mut self, command: &mut Command) -> std::io::Result<Child> {
+            let slave = self.slave.take().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    "a program already runs on this pseudo-terminal",
+                )
+            })?;
+            command
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?))
+                .stderr(Stdio::from(slave));
+            crate::process::reset_job_signals(command);
+            // SAFETY: only async-signal-safe calls run between fork and exec
+            unsafe {
+                command.pre_exec(|| {
+                    // A session of its own makes the pseudo-terminal its
+                    // controlling terminal, with Ctrl-C and Ctrl-Z
+                    nix::unistd::setsid()?;
+                    if libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY as _, 0) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            let child = command.spawn();
+            // Drop the command's copies of the slave end
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            child
+        }
+
+        pub(super) fn relay(
+            self,
+            child: &mut Child,
+            mut watch: ResizeWatch,
+        ) -> HalResult<ExitStatus> {
+            let stdin = std::io::stdin();
+            let _raw = RawMode::enter(&stdin);
+            let mut stdout = std::io::stdout();
+            let mut input_open = true;
+            let mut buffer = [0u8; 4096];
+            loop {
+                if let Some(size) = watch.changed() {
+                    let _ = self.resize(size);
+                }
+                let status = child.try_wait()?;
+                let mut fds = vec![PollFd::new(&self.master, PollFlags::POLLIN)];
+                if input_open {
+                    fds.push(PollFd::new(&stdin, PollFlags::POLLIN));
+                }
+                // Once the program has exited, only what it left is copied
+                let timeout = if status.is_some() {
+                    0
+                } else {
+                    TICK.as_millis() as libc::c_int
+                };
+                match poll(&mut fds, timeout) {
+                    Ok(_) => {}
+                    Err(Errno::EINTR) => continue,
+                    Err(e) => return Err(failed("poll", e)),
+                }
+                let ready = |fd: &PollFd| {
+                    fd.revents().is_some_and(|events| {
+                        events
+                            .intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR)
+                    })
+                };
+                let output_ready = ready(&fds[0]);
+                let input_ready = fds.get(1).is_some_and(ready);
+                drop(fds);
+
+                if output_ready {
+                    match (&self.master).read(&mut buffer) {
+                        Ok(n) if n > 0 => {
+                            stdout.write_all(&buffer[..n])?;
+                            stdout.flush()?;
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                        // EIO: every program on the terminal has closed it
+                        _ => return Ok(child.wait()?),
+                    }
+                } else if let Some(status) = status {
+                    return Ok(status);
+                }
+                if input_ready {
+                    match nix::unistd::read(stdin.as_raw_fd(), &mut buffer) {
+                        Ok(0) | Err(Errno::EIO) => {
+                            // End of input reads as Ctrl-D on the terminal
+                            input_open = false;
+                            let _ = (&self.master).write_all(&[0x04]);
+                        }
+                        Ok(n) => (&self.master).write_all(&buffer[..n])?,
+                        Err(Errno::EINTR | Errno::EAGAIN) => {}
+                        Err(e) => return Err(failed("read", e)),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Raw mode on a terminal, restored when dropped
+    struct RawMode<'a, Fd: AsFd> {
+        fd: &'a Fd,
+        saved: Termios,
+    }
+
+    impl<'a, Fd: AsFd> RawMode<'a, Fd> {
+        /// `None` when `fd` is not a terminal
+        fn enter(fd: &'a Fd) -> Option<Self> {
+            let saved = tcgetattr(fd.as_fd()).ok()?;
+            let mut raw = saved.clone();
+            cfmakeraw(&mut raw);
+            tcsetattr(fd.as_fd(), SetArg::TCSANOW, &raw).ok()?;
+            Some(RawMode { fd, saved })
+        }
+    }
+
+    impl<Fd: AsFd> Drop for RawMode<'_, Fd> {
+        fn drop(&mut self) {
+            let _ = tcsetattr(self.fd.as_fd(), SetArg::TCSADRAIN, &self.saved);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::TICK;
+    use crate::error::{HalError, HalResult};
+    use crate::terminal::{ResizeWatch, TerminalSize};
+    use std::ffi::{c_void, OsStr, OsString};
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use std::os::windows::process::ExitStatusExt;
+    use std::process::{Command, ExitStatus};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0};
+    use windows_sys::Win32::System::Console::{
+        ClosePseudoConsole, CreatePseudoConsole, GetConsoleMode, GetStdHandle, ResizePseudoConsole,
+        SetConsoleMode, COORD, ENABLE_VIRTUAL_TERMINAL_INPUT, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+        HPCON, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+    use windows_sys::Win32::System::Threading::{
+        CreateProcessW, DeleteProcThreadAttributeList, GetExitCodeProcess,
+        InitializeProcThreadAttributeList, TerminateProcess, UpdateProcThreadAttribute,
+        WaitForSingleObject, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, INFINITE,
+        PROCESS_INFORMATION, PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE, STARTF_USESTDHANDLES,
+        STARTUPINFOEXW,
+    };
+
+    fn last_error(operation: &str, pid: Option<u32>) -> HalError {
+        HalError::process_error(operation, pid, &std::io::Error::last_os_error().to_string())
+    }
+
+    fn coord(size: TerminalSize) -> COORD {
+        COORD {
+            X: size.columns.min(i16::MAX as u16) as i16,
+            Y: size.rows.min(i16::MAX as u16) as i16,
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct Pty {
+        console: HPCON,
+        /// What is written here is typed on the pseudo console
+        input: File,
+        /// What the pseudo console shows
+        output: File,
+        spawned: bool,
+    }
+
+    impl Pty {
+        pub(super) fn open(size: TerminalSize) -> HalResult<Self> {
+            let (mut input_read, mut input_write): (HANDLE, HANDLE) = (0, 0);
+            let (mut output_read, mut output_write): (HANDLE, HANDLE) = (0, 0);
+            // SAFETY: the out pointers are valid; null attributes make
+            // handles that are not inherited
+            unsafe {
+                if CreatePipe(&mut input_read, &mut input_write, std::ptr::null(), 0) == 0 {
+                    return Err(last_error("CreatePipe", None));
+                }
+                if CreatePipe(&mut output_read, &mut output_write, std::ptr::null(), 0) == 0 {
+                    let error = last_error("CreatePipe", None);
+                    CloseHandle(input_read);
+                    CloseHandle(input_write);
+                    return Err(error);
+                }
+            }
+            let mut console: HPCON = 0;
+            // SAFETY: the pipe ends are open; the pseudo console duplicates
+            // the ones it keeps, so ours are closed either way
+            let result = unsafe {
+                let result =
+                    CreatePseudoConsole(coord(size), input_read, output_write, 0, &mut console);
+                CloseHandle(input_read);
+                CloseHandle(output_write);
+                result
+            };
+            // SAFETY: the handles are owned here and handed to the files
+            let (input, output) = unsafe {
+                (
+                    File::from_raw_handle(input_write as RawHandle),
+                    File::from_raw_handle(output_read as RawHandle),
+                )
+            };
+            if result < 0 {
+                return Err(HalError::process_error(
+                    "CreatePseudoConsole",
+                    None,
+                    &std::io::Error::from_raw_os_error(result).to_string(),
+                ));
+            }
+            Ok(Pty {
+                console,
+                input,
+                output,
+                spawned: false,
+            })
+        }
+
+        pub(super) fn resize(&self, size: TerminalSize) -> HalResult<()> {
+            // SAFETY: the pseudo console stays open while `self` lives
+            let result = unsafe { ResizePseudoConsole(self.console, coord(size)) };
+            if result < 0 {
+                return Err(HalError::process_error(
+                    "ResizePseudoConsole",
+                    None,
+                    &std::io::Error::from_raw_os_error(result).to_string(),
+                ));
+            }
+            Ok(())
+        }
+
+        pub(super) fn spawn(&mut self, command: &mut Command) -> std::io::Result<Child> {
+            if self.spawned {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    "a program already runs on this pseudo console",
+                ));
+            }
+            let mut command_line = command_line(command);
+            let mut environment = environment(command);
+            let directory = command.get_current_dir().map(wide);
+
+            let mut size = 0;
+            // SAFETY: the first call only reports the size the list needs
+            unsafe { InitializeProcThreadAttributeList(std::ptr::null_mut(), 1, 0, &mut size) };
+            let mut attributes = vec![0u8; size];
+            let list = attributes.as_mut_ptr() as *mut c_void;
+            // SAFETY: `list` points at `size` bytes, and the pseudo console
+            // handle outlives the process creation
+            unsafe {
+                if InitializeProcThreadAttributeList(list, 1, 0, &mut size) == 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if UpdateProcThreadAttribute(
+                    list,
+                    0,
+                    PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE as usize,
+                    self.console as *const c_void,
+                    std::mem::size_of::<HPCON>(),
+                    std::ptr::null_mut(),
+                    std::ptr::null(),
+                ) == 0
+                {
+                    let error = std::io::Error::last_os_error();
+                    DeleteProcThreadAttributeList(list);
+                    return Err(error);
+                }
+            }
+
+            // SAFETY: the structures are plain data for which all zeros is
+            // valid
+            let mut startup: STARTUPINFOEXW = unsafe { std::mem::zeroed() };
+            startup.StartupInfo.cb = std::mem::size_of::<STARTUPINFOEXW>() as u32;
+            // No standard handles: the program gets the pseudo console's
+            startup.StartupInfo.dwFlags = STARTF_USESTDHANDLES;
+            startup.lpAttributeList = list;
+            let mut process: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
+            // SAFETY: every buffer is NUL-terminated and outlives the call
+            let created = unsafe {
+                let created = CreateProcessW(
+                    std::ptr::null(),
+                    command_line.as_mut_ptr(),
+                    std::ptr::null(),
+                    std::ptr::null(),
+                    0,
+                    EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+                    environment.as_mut_ptr() as *const c_void,
+                    directory
+                        .as_ref()
+                        .map_or(std::ptr::null(), |dir| dir.as_ptr()),
+                    &startup.StartupInfo,
+                    &mut process,
+                );
+                DeleteProcThreadAttributeList(list);
+                created
+            };
+            if created == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // SAFETY: the thread handle is ours and not needed
+            unsafe { CloseHandle(process.hThread) };
+            self.spawned = true;
+            Ok(Child {
+                process: process.hProcess,
+                id: process.dwProcessId,
+            })
+        }
+
+        pub(super) fn relay(
+            self,
+            child: &mut Child,
+            mut watch: ResizeWatch,
+        ) -> HalResult<ExitStatus> {
+            let _vt = VtModes::enter();
+            let mut output = self.output.try_clone()?;
+            let copy_output = std::thread::spawn(move || {
+                let mut stdout = std::io::stdout();
+                let mut buffer = [0u8; 4096];
+                while let Ok(n) = output.read(&mut buffer) {
+                    if n == 0 || stdout.write_all(&buffer[..n]).is_err() {
+                        break;
+                    }
+                    let _ = stdout.flush();
+                }
+            });
+            // A console read cannot be interrupted: the thread ends at the
+            // next input after the program, when writing to it fails
+            let mut input = self.input.try_clone()?;
+            std::thread::spawn(move || {
+                let mut stdin = std::io::stdin();
+                let mut buffer = [0u8; 4096];
+                while let Ok(n) = stdin.read(&mut buffer) {
+                    if n == 0 || input.write_all(&buffer[..n]).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let status = loop {
+                if let Some(size) = watch.changed() {
+                    let _ = self.resize(size);
+                }
+                if let Some(status) = child.wait_for(TICK.as_millis() as u32)? {
+                    break status;
+                }
+            };
+            // Closing the pseudo console ends its output once it is drained
+            drop(self);
+            let _ = copy_output.join();
+            Ok(status)
+        }
+    }
+
+    impl Drop for Pty {
+        fn drop(&mut self) {
+            // SAFETY: the pseudo console is owned by `self` and closed once
+            unsafe { ClosePseudoConsole(self.console) };
+        }
+    }
+
+    /// The console modes that pass escape sequences through, restored when
+    /// dropped
+    struct VtModes {
+        saved: [(HANDLE, u32); 2],
+    }
+
+    impl VtModes {
+        fn enter() -> Self {
+            let mut saved = [(0, 0); 2];
+            for (slot, (which, vt)) in saved.iter_mut().zip([
+                (STD_INPUT_HANDLE, ENABLE_VIRTUAL_TERMINAL_INPUT),
+                (STD_OUTPUT_HANDLE, ENABLE_VIRTUAL_TERMINAL_PROCESSING),
+            ]) {
+                // SAFETY: `mode` is a valid out pointer; a handle that is
+                // not a console fails both calls harmlessly
+                unsafe {
+                    let handle = GetStdHandle(which);
+                    let mut mode = 0;
+                    if GetConsoleMode(handle, &mut mode) != 0 {
+                        let raw = if which == STD_INPUT_HANDLE {
+                            vt
+                        } else {
+                            mode | vt
+                        };
+                        SetConsoleMode(handle, raw);
+                        *slot = (handle, mode);
+                    }
+                }
+            }
+            VtModes { saved }
+        }
+    }
+
+    impl Drop for VtModes {
+        fn drop(&mut self) {
+            for (handle, mode) in self.saved {
+                if handle != 0 {
+                    // SAFETY: the handle came from GetStdHandle
+                    unsafe { SetConsoleMode(handle, mode) };
+                }
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct Child {
+        process: HANDLE,
+        id: u32,
+    }
+
+    impl Child {
+        pub(super) fn id(&self) -> u32 {
+            self.id
+        }
+
+        /// The exit status once the process has exited within `millis`
+        fn wait_for(&mut self, millis: u32) -> std::io::Result<Option<ExitStatus>> {
+            // SAFETY: the process handle is owned by `self`
+            if unsafe { WaitForSingleObject(self.process, millis) } != WAIT_OBJECT_0 {
+                return Ok(None);
+            }
+            let mut code = 0;
+            // SAFETY: `code` is a valid out pointer
+            if unsafe { GetExitCodeProcess(self.process, &mut code) } == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Some(ExitStatus::from_raw(code)))
+        }
+
+        pub(super) fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+            self.wait_for(0)
+        }
+
+        pub(super) fn wait(&mut self) -> std::io::Result<ExitStatus> {
+            loop {
+                if let Some(status) = self.wait_for(INFINITE)? {
+                    return Ok(status);
+                }
+            }
+        }
+
+        pub(super) fn kill(&mut self) -> std::io::Result<()> {
+            // SAFETY: the process handle is owned by `self`
+            if unsafe { TerminateProcess(self.process, 1) } == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Child {
+        fn drop(&mut self) {
+            // SAFETY: the process handle is owned by `self` and closed once
+            unsafe { CloseHandle(self.process) };
+        }
+    }
+
+    fn wide(s: impl AsRef<OsStr>) -> Vec<u16> {
+        s.as_ref().encode_wide().chain(Some(0)).collect()
+    }
+
+    /// The program and its arguments, quoted the way the C runtime splits
+    /// them again
+    fn command_line(command: &Command) -> Vec<u16> {
+        let mut line = OsString::new();
+        for (i, arg) in std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .enumerate()
+        {
+            if i > 0 {
+                line.push(" ");
+            }
+            line.push(quote(&arg.to_string_lossy()));
+        }
+        wide(line)
+    }
+
+    fn quote(arg: &str) -> String {
+        if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '"']) {
+            return arg.to_string();
+        }
+        let mut quoted = String::from('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            if c == '\\' {
+                backslashes += 1;
+                continue;
+            }
+            // Backslashes are literal unless they come before a quote
+            let escaped = if c == '"' {
+                backslashes * 2 + 1
+            } else {
+                backslashes
+            };
+            quoted.extend(std::iter::repeat('\\').take(escaped));
+            quoted.push(c);
+            backslashes = 0;
+        }
+        quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+        quoted.push('"');
+        quoted
+    }
+
+    /// The shell's environment with the command's own variables applied,
+    /// as a sorted `NAME=value` block
+    fn environment(command: &Command) -> Vec<u16> {
+        fn var(key: &OsStr, value: &OsStr) -> OsString {
+            let mut var = key.to_os_string();
+            var.push("=");
+            var.push(value);
+            var
+        }
+
+        // Names are case-insensitive, and the block is sorted by them
+        let mut vars: std::collections::BTreeMap<OsString, OsString> = std::env::vars_os()
+            .map(|(key, value)| (key.to_ascii_uppercase(), var(&key, &value)))
+            .collect();
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => vars.insert(key.to_ascii_uppercase(), var(key, value)),
+                None => vars.remove(&key.to_ascii_uppercase()),
+            };
+        }
+        let mut block: Vec<u16> = vars.values().flat_map(|var| wide(var)).collect();
+        block.push(0);
+        block
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use crate::error::{HalError, HalResult};
+    use crate::terminal::{ResizeWatch, TerminalSize};
+    use std::process::{Command, ExitStatus};
+
+    pub(super) type Child = std::process::Child;
+
+    #[derive(Debug)]
+    pub(super) struct Pty;
+
+    impl Pty {
+        pub(super) fn open(_size: TerminalSize) -> HalResult<Self> {
+            Err(HalError::unsupported(
+                "pseudo-terminals are not supported on this platform",
+            ))
+        }
+
+        pub(super) fn resize(&self, _size: TerminalSize) -> HalResult<()> {
+            Ok(())
+        }
+
+        pub(super) fn spawn(&mut self, command: &mut Command) -> std::io::Result<Child> {
+            command.spawn()
+        }
+
+        pub(super) fn relay(self, child: &mut Child, _watch: ResizeWatch) -> HalResult<ExitStatus> {
+            Ok(child.wait()?)
+        }
+    }
+}
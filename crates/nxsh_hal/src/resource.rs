@@ -0,0 +1,322 @@
+//! Resource limits and the file creation mask
+//!
+//! On Unix both are attributes of the shell process itself, so whatever is
+//! set here is inherited by every command the executor spawns. Windows has
+//! no per-process equivalent: the limits a Job Object can express are kept
+//! here and applied to each foreground job's object, and the mask is only
+//! remembered so the shell can report it back.
+
+use crate::error::{HalError, HalResult};
+
+/// A limited resource, named after its `RLIMIT_*` counterpart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// Largest core file, in bytes
+    CoreSize,
+    /// Largest data segment, in bytes
+    DataSize,
+    /// Highest nice value that may be requested
+    Nice,
+    /// Largest file that may be written, in bytes
+    FileSize,
+    /// Signals that may be queued
+    PendingSignals,
+    /// Memory that may be locked, in bytes
+    LockedMemory,
+    /// Largest resident set, in bytes
+    ResidentSet,
+    /// Open file descriptors
+    OpenFiles,
+    /// Bytes in POSIX message queues
+    MessageQueue,
+    /// Highest real-time priority
+    RealtimePriority,
+    /// Largest stack, in bytes
+    StackSize,
+    /// CPU time, in seconds
+    CpuTime,
+    /// Processes per user
+    Processes,
+    /// Largest address space, in bytes
+    VirtualMemory,
+    /// File locks held
+    FileLocks,
+}
+
+impl Resource {
+    /// Every resource, in the order `ulimit -a` lists them
+    pub const ALL: [Resource; 15] = [
+        Resource::CoreSize,
+        Resource::DataSize,
+        Resource::Nice,
+        Resource::FileSize,
+        Resource::PendingSignals,
+        Resource::LockedMemory,
+        Resource::ResidentSet,
+        Resource::OpenFiles,
+        Resource::MessageQueue,
+        Resource::RealtimePriority,
+        Resource::StackSize,
+        Resource::CpuTime,
+        Resource::Processes,
+        Resource::VirtualMemory,
+        Resource::FileLocks,
+    ];
+
+    /// Whether the limit can be read and set on this platform
+    pub fn is_supported(self) -> bool {
+        #[cfg(unix)]
+        {
+            rlimit::resource(self).is_some()
+        }
+        #[cfg(windows)]
+        {
+            matches!(
+                self,
+                Resource::CpuTime | Resource::Processes | Resource::VirtualMemory
+            )
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            false
+        }
+    }
+}
+
+/// A soft and hard limit, where `None` means unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimit {
+    /// The limit in force
+    pub soft: Option<u64>,
+    /// The ceiling the soft limit may be raised to
+    pub hard: Option<u64>,
+}
+
+impl ResourceLimit {
+    pub const UNLIMITED: ResourceLimit = ResourceLimit {
+        soft: None,
+        hard: None,
+    };
+}
+
+/// Read the current limits on a resource
+pub fn get_limit(resource: Resource) -> HalResult<ResourceLimit> {
+    #[cfg(unix)]
+    {
+        rlimit::get(resource)
+    }
+    #[cfg(windows)]
+    {
+        job_limits::get(resource)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(unsupported(resource))
+    }
+}
+
+/// Change the limits on a resource for the shell and the commands it runs
+pub fn set_limit(resource: Resource, limit: ResourceLimit) -> HalResult<()> {
+    if let (Some(soft), Some(hard)) = (limit.soft, limit.hard) {
+        if soft > hard {
+            return Err(HalError::invalid("soft limit exceeds hard limit"));
+        }
+    }
+    #[cfg(unix)]
+    {
+        rlimit::set(resource, limit)
+    }
+    #[cfg(windows)]
+    {
+        job_limits::set(resource, limit)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(unsupported(resource))
+    }
+}
+
+fn unsupported(resource: Resource) -> HalError {
+    HalError::unsupported(&format!("{resource:?} limit on this platform"))
+}
+
+/// The current file creation mask
+// mode_t is u32 on Linux but u16 on macOS
+#[allow(clippy::unnecessary_cast)]
+pub fn umask() -> u32 {
+    #[cfg(unix)]
+    {
+        use nix::sys::stat::{umask, Mode};
+        // Reading the mask means setting it, so hold the lock across both
+        let _guard = UMASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let old = umask(Mode::empty());
+        umask(old);
+        old.bits() as u32
+    }
+    #[cfg(not(unix))]
+    {
+        UMASK.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// Set the file creation mask, returning the previous one
+#[allow(clippy::unnecessary_cast)]
+pub fn set_umask(mask: u32) -> u32 {
+    let mask = mask & 0o777;
+    #[cfg(unix)]
+    {
+        use nix::sys::stat::{umask, Mode};
+        let _guard = UMASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        umask(Mode::from_bits_truncate(mask as nix::libc::mode_t)).bits() as u32
+    }
+    #[cfg(not(unix))]
+    {
+        UMASK.swap(mask, std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(unix)]
+static UMASK_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(not(unix))]
+static UMASK: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0o022);
+
+#[cfg(unix)]
+mod rlimit {
+    use super::{Resource, ResourceLimit};
+    use crate::error::{HalError, HalResult};
+    use nix::libc::{rlim_t, RLIM_INFINITY};
+    use nix::sys::resource::{getrlimit, setrlimit, Resource as Rlimit};
+
+    pub(super) fn resource(resource: Resource) -> Option<Rlimit> {
+        Some(match resource {
+            Resource::CoreSize => Rlimit::RLIMIT_CORE,
+            Resource::DataSize => Rlimit::RLIMIT_DATA,
+            Resource::FileSize => Rlimit::RLIMIT_FSIZE,
+            Resource::OpenFiles => Rlimit::RLIMIT_NOFILE,
+            Resource::StackSize => Rlimit::RLIMIT_STACK,
+            Resource::CpuTime => Rlimit::RLIMIT_CPU,
+            #[cfg(not(any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+            Resource::VirtualMemory => Rlimit::RLIMIT_AS,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::Processes => Rlimit::RLIMIT_NPROC,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::LockedMemory => Rlimit::RLIMIT_MEMLOCK,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::ResidentSet => Rlimit::RLIMIT_RSS,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::FileLocks => Rlimit::RLIMIT_LOCKS,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::MessageQueue => Rlimit::RLIMIT_MSGQUEUE,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::Nice => Rlimit::RLIMIT_NICE,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::RealtimePriority => Rlimit::RLIMIT_RTPRIO,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Resource::PendingSignals => Rlimit::RLIMIT_SIGPENDING,
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        })
+    }
+
+    pub(super) fn get(resource: Resource) -> HalResult<ResourceLimit> {
+        let rlimit = self::resource(resource).ok_or_else(|| super::unsupported(resource))?;
+        let (soft, hard) =
+            getrlimit(rlimit).map_err(|e| HalError::io_error("getrlimit", None, e.into()))?;
+        Ok(ResourceLimit {
+            soft: from_raw(soft),
+            hard: from_raw(hard),
+        })
+    }
+
+    pub(super) fn set(resource: Resource, limit: ResourceLimit) -> HalResult<()> {
+        let rlimit = self::resource(resource).ok_or_else(|| super::unsupported(resource))?;
+        setrlimit(rlimit, to_raw(limit.soft), to_raw(limit.hard))
+            .map_err(|e| HalError::io_error("setrlimit", None, e.into()))
+    }
+
+    // rlim_t is u64 on most targets but not all
+    #[allow(clippy::unnecessary_cast)]
+    fn from_raw(raw: rlim_t) -> Option<u64> {
+        (raw != RLIM_INFINITY).then_some(raw as u64)
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn to_raw(limit: Option<u64>) -> rlim_t {
+        match limit {
+            Some(value) if value < RLIM_INFINITY as u64 => value as rlim_t,
+            _ => RLIM_INFINITY,
+        }
+    }
+}
+
+/// The limits applied to foreground jobs through their Job Objects
+#[cfg(windows)]
+pub(crate) mod job_limits {
+    use super::{Resource, ResourceLimit};
+    use crate::error::HalResult;
+    use std::sync::Mutex;
+
+    static LIMITS: Mutex<Vec<(Resource, ResourceLimit)>> = Mutex::new(Vec::new());
+
+    pub(crate) fn get(resource: Resource) -> HalResult<ResourceLimit> {
+        if !resource.is_supported() {
+            return Err(super::unsupported(resource));
+        }
+        let limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(limits
+            .iter()
+            .find(|(r, _)| *r == resource)
+            .map(|(_, limit)| *limit)
+            .unwrap_or(ResourceLimit::UNLIMITED))
+    }
+
+    pub(crate) fn set(resource: Resource, limit: ResourceLimit) -> HalResult<()> {
+        let current = get(resource)?;
+        // Without a real hard limit, only the shell's own record stops a
+        // raise past it
+        if let Some(hard) = current.hard {
+            if limit.hard.unwrap_or(u64::MAX) > hard {
+                return Err(crate::error::HalError::security_error(
+                    "set_limit",
+                    "raise a hard limit",
+                    "Operation not permitted",
+                ));
+            }
+        }
+        let mut limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+        limits.retain(|(r, _)| *r != resource);
+        limits.push((resource, limit));
+        Ok(())
+    }
+
+    /// The soft limit in force for a resource, if any
+    pub(crate) fn soft(resource: Resource) -> Option<u64> {
+        get(resource).ok().and_then(|limit| limit.soft)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn umask_round_trips() {
+        let old = set_umask(0o027);
+        assert_eq!(umask(), 0o027);
+        assert_eq!(set_umask(old), 0o027);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn soft_limit_can_be_lowered_and_restored() {
+        let old = get_limit(Resource::CoreSize).unwrap();
+        let lowered = ResourceLimit {
+            soft: Some(0),
+            hard: old.hard,
+        };
+        set_limit(Resource::CoreSize, lowered).unwrap();
+        assert_eq!(get_limit(Resource::CoreSize).unwrap(), lowered);
+        set_limit(Resource::CoreSize, old).unwrap();
+    }
+}
@@ -0,0 +1,567 @@
+//! Running one command under a sandbox policy
+//!
+//! A [`SandboxPolicy`] limits what a single child process may do, without
+//! touching the shell itself:
+//!
+//! - CPU time, address space, open files and processes are capped with
+//!   resource limits on Unix and with a Job Object on Windows
+//! - the file system is narrowed with Landlock on Linux, either made
+//!   read-only except for some paths or hidden except for some paths
+//! - the network is cut off with a seccomp filter on Linux that refuses
+//!   IPv4, IPv6 and raw packet sockets, leaving Unix sockets alone
+//! - a wall-clock timeout kills the command when it runs too long
+//!
+//! A restriction the platform cannot enforce is an error rather than being
+//! skipped, so a policy either holds or the command does not run.
+
+use crate::error::{HalError, HalResult};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// What a sandboxed command may do; the default restricts nothing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxPolicy {
+    /// Refuse network sockets
+    pub no_network: bool,
+    /// When set, only these paths and what is beneath them may be
+    /// written; everything else is read-only
+    pub writable: Option<Vec<PathBuf>>,
+    /// When set, only these paths and what is beneath them may be read or
+    /// executed, besides the writable ones
+    pub readable: Option<Vec<PathBuf>>,
+    /// CPU time per process
+    pub cpu_time: Option<Duration>,
+    /// Address space per process, in bytes
+    pub memory: Option<u64>,
+    /// Open file descriptors per process
+    pub open_files: Option<u64>,
+    /// Processes, counted per user on Unix and per job on Windows
+    pub processes: Option<u64>,
+    /// Wall-clock time before the command is killed
+    pub timeout: Option<Duration>,
+}
+
+impl SandboxPolicy {
+    /// Whether the policy narrows file system access
+    pub fn restricts_files(&self) -> bool {
+        self.writable.is_some() || self.readable.is_some()
+    }
+}
+
+/// How a sandboxed command ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxRun {
+    /// The exit code, or 128 plus the signal that killed the command
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// The command was killed for running past the timeout
+    pub timed_out: bool,
+}
+
+/// Run `command` under `policy` and wait for it, capturing its output.
+/// Standard input is inherited.
+pub fn run(policy: &SandboxPolicy, mut command: Command) -> HalResult<SandboxRun> {
+    command
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    let child = unix::spawn(policy, &mut command)?;
+    #[cfg(windows)]
+    let (child, job) = windows::spawn(policy, &mut command)?;
+    #[cfg(not(any(unix, windows)))]
+    let child: std::process::Child = {
+        let _ = (policy, &mut command);
+        return Err(HalError::unsupported("sandboxing on this platform"));
+    };
+
+    let mut child = child;
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+    let deadline = policy.timeout.map(|timeout| Instant::now() + timeout);
+    let mut timed_out = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(e) => return Err(HalError::io_error("wait", None, e)),
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            timed_out = true;
+            #[cfg(windows)]
+            let _ = job.terminate(1);
+            let _ = child.kill();
+            break child
+                .wait()
+                .map_err(|e| HalError::io_error("wait", None, e))?;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    let collect = |reader: Option<std::thread::JoinHandle<Vec<u8>>>| {
+        reader
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+    #[cfg(unix)]
+    let exit_code = std::os::unix::process::ExitStatusExt::signal(&status)
+        .map(|signal| 128 + signal)
+        .or(status.code())
+        .unwrap_or(1);
+    #[cfg(not(unix))]
+    let exit_code = status.code().unwrap_or(1);
+    Ok(SandboxRun {
+        exit_code,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+        timed_out,
+    })
+}
+
+/// Read a pipe to its end on a thread of its own, so a full pipe never
+/// stalls the command while its other stream is being waited on
+fn drain<R: Read + Send + 'static>(mut pipe: R) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::SandboxPolicy;
+    use crate::error::{HalError, HalResult};
+    use nix::libc::rlim_t;
+    use nix::sys::resource::{setrlimit, Resource};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+
+    pub(super) fn spawn(policy: &SandboxPolicy, command: &mut Command) -> HalResult<Child> {
+        let limits = limits(policy)?;
+
+        #[cfg(target_os = "linux")]
+        let landlock = if policy.restricts_files() {
+            Some(super::linux::Ruleset::new(policy)?)
+        } else {
+            None
+        };
+        #[cfg(not(target_os = "linux"))]
+        if policy.restricts_files() {
+            return Err(HalError::unsupported(
+                "file system sandboxing outside Linux",
+            ));
+        }
+
+        #[cfg(target_os = "linux")]
+        let filter = if policy.no_network {
+            Some(super::linux::network_filter()?)
+        } else {
+            None
+        };
+        #[cfg(not(target_os = "linux"))]
+        if policy.no_network {
+            return Err(HalError::unsupported("network sandboxing outside Linux"));
+        }
+
+        #[cfg(target_os = "linux")]
+        let ruleset_fd = landlock.as_ref().map(|ruleset| ruleset.fd());
+        // SAFETY: the hook only makes system calls, which are safe between
+        // fork and exec, and allocates nothing
+        unsafe {
+            command.pre_exec(move || {
+                for &(resource, value) in &limits {
+                    setrlimit(resource, value, value)?;
+                }
+                #[cfg(target_os = "linux")]
+                super::linux::restrict_self(ruleset_fd, filter.as_deref())?;
+                Ok(())
+            });
+        }
+        let child = command
+            .spawn()
+            .map_err(|e| HalError::io_error("spawn", None, e));
+        // The ruleset is only needed until the child has applied it
+        #[cfg(target_os = "linux")]
+        drop(landlock);
+        child
+    }
+
+    /// The resource limits to set in the child, as soft and hard at once
+    #[allow(clippy::unnecessary_cast)]
+    fn limits(policy: &SandboxPolicy) -> HalResult<Vec<(Resource, rlim_t)>> {
+        let mut limits = Vec::new();
+        if let Some(cpu_time) = policy.cpu_time {
+            // Whole seconds, rounded up so a short limit is not no limit
+            let seconds = cpu_time.as_secs() + u64::from(cpu_time.subsec_nanos() > 0);
+            limits.push((Resource::RLIMIT_CPU, seconds.max(1) as rlim_t));
+        }
+        if let Some(memory) = policy.memory {
+            #[cfg(not(any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+            limits.push((Resource::RLIMIT_AS, memory as rlim_t));
+            #[cfg(any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+            limits.push((Resource::RLIMIT_DATA, memory as rlim_t));
+        }
+        if let Some(files) = policy.open_files {
+            limits.push((Resource::RLIMIT_NOFILE, files as rlim_t));
+        }
+        if let Some(processes) = policy.processes {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            limits.push((Resource::RLIMIT_NPROC, processes as rlim_t));
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            {
+                let _ = processes;
+                return Err(HalError::unsupported("process limits on this platform"));
+            }
+        }
+        Ok(limits)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::SandboxPolicy;
+    use crate::error::{HalError, HalResult};
+    use nix::fcntl::{open, OFlag};
+    use nix::libc;
+    use nix::sys::stat::Mode;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::path::Path;
+
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+    const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+    const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+    const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+    const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+    const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+    const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+    const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+    const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+    const READ_ACCESS: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+    /// The rights that apply to a file rather than a directory
+    const FILE_ACCESS: u64 =
+        ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+    const CREATE_RULESET_VERSION: u32 = 1 << 0;
+    const RULE_PATH_BENEATH: libc::c_int = 1;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// Every write right the running kernel's Landlock knows
+    fn write_access(abi: i64) -> u64 {
+        let mut access = ACCESS_FS_WRITE_FILE
+            | ACCESS_FS_REMOVE_DIR
+            | ACCESS_FS_REMOVE_FILE
+            | ACCESS_FS_MAKE_CHAR
+            | ACCESS_FS_MAKE_DIR
+            | ACCESS_FS_MAKE_REG
+            | ACCESS_FS_MAKE_SOCK
+            | ACCESS_FS_MAKE_FIFO
+            | ACCESS_FS_MAKE_BLOCK
+            | ACCESS_FS_MAKE_SYM;
+        if abi >= 2 {
+            access |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            access |= ACCESS_FS_TRUNCATE;
+        }
+        access
+    }
+
+    /// A Landlock ruleset built in the shell, for the child to restrict
+    /// itself with
+    pub(super) struct Ruleset {
+        fd: OwnedFd,
+    }
+
+    impl Ruleset {
+        pub(super) fn new(policy: &SandboxPolicy) -> HalResult<Self> {
+            // SAFETY: asking for the ABI version takes no pointers
+            let abi = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_create_ruleset,
+                    std::ptr::null::<RulesetAttr>(),
+                    0usize,
+                    CREATE_RULESET_VERSION,
+                )
+            };
+            if abi < 1 {
+                return Err(HalError::unsupported(
+                    "file system sandboxing without Landlock (Linux 5.13 or later)",
+                ));
+            }
+            let write = write_access(abi);
+            let mut handled = 0;
+            if policy.writable.is_some() {
+                handled |= write;
+            }
+            if policy.readable.is_some() {
+                handled |= READ_ACCESS;
+            }
+
+            let attr = RulesetAttr {
+                handled_access_fs: handled,
+            };
+            // SAFETY: the pointer and size describe `attr`
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_create_ruleset,
+                    &attr as *const RulesetAttr,
+                    std::mem::size_of::<RulesetAttr>(),
+                    0u32,
+                )
+            };
+            if fd < 0 {
+                return Err(last_error("landlock_create_ruleset", None));
+            }
+            // SAFETY: the descriptor was just created and is owned by no one else
+            let ruleset = Ruleset {
+                fd: unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+            };
+
+            // Writable paths stay readable even when reading is narrowed
+            for path in policy.writable.iter().flatten() {
+                ruleset.allow(path, (write | READ_ACCESS) & handled)?;
+            }
+            for path in policy.readable.iter().flatten() {
+                ruleset.allow(path, READ_ACCESS & handled)?;
+            }
+            Ok(ruleset)
+        }
+
+        fn allow(&self, path: &Path, access: u64) -> HalResult<()> {
+            let shown = path.to_string_lossy();
+            let parent = open(path, OFlag::O_PATH | OFlag::O_CLOEXEC, Mode::empty())
+                .map_err(|e| HalError::io_error("open", Some(&*shown), e.into()))?;
+            // SAFETY: `open` returned a new descriptor that nothing else owns
+            let parent = unsafe { OwnedFd::from_raw_fd(parent) };
+            let is_dir = std::fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false);
+            let rule = PathBeneathAttr {
+                allowed_access: if is_dir { access } else { access & FILE_ACCESS },
+                parent_fd: parent.as_raw_fd(),
+            };
+            // SAFETY: the rule is a valid path-beneath attribute for the call
+            let result = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    self.fd.as_raw_fd(),
+                    RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0u32,
+                )
+            };
+            if result < 0 {
+                return Err(last_error("landlock_add_rule", Some(&*shown)));
+            }
+            Ok(())
+        }
+
+        pub(super) fn fd(&self) -> RawFd {
+            self.fd.as_raw_fd()
+        }
+    }
+
+    // BPF instruction parts, from linux/bpf_common.h
+    const BPF_LD: u16 = 0x00;
+    const BPF_W: u16 = 0x00;
+    const BPF_ABS: u16 = 0x20;
+    const BPF_JMP: u16 = 0x05;
+    const BPF_JEQ: u16 = 0x10;
+    #[cfg(target_arch = "x86_64")]
+    const BPF_JGE: u16 = 0x30;
+    const BPF_K: u16 = 0x00;
+    const BPF_RET: u16 = 0x06;
+
+    // Offsets into struct seccomp_data
+    const SYSCALL_NR: u32 = 0;
+    const ARCH: u32 = 4;
+    /// The low half of the first argument on little-endian targets
+    const FIRST_ARG: u32 = 16;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+    #[cfg(target_arch = "x86_64")]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    fn statement(code: u16, k: u32) -> libc::sock_filter {
+        libc::sock_filter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code, jt, jf, k }
+    }
+
+    /// A seccomp filter refusing IPv4, IPv6 and packet sockets with EACCES
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub(super) fn network_filter() -> HalResult<Vec<libc::sock_filter>> {
+        let deny = libc::SECCOMP_RET_ERRNO | libc::EACCES as u32;
+        let allow = libc::SECCOMP_RET_ALLOW;
+        let mut program = vec![
+            // System calls of another ABI could get around the filter
+            statement(BPF_LD | BPF_W | BPF_ABS, ARCH),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+            statement(BPF_RET | BPF_K, deny),
+            statement(BPF_LD | BPF_W | BPF_ABS, SYSCALL_NR),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        program.extend([
+            jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+            statement(BPF_RET | BPF_K, deny),
+        ]);
+        program.extend([
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_socket as u32, 1, 0),
+            statement(BPF_RET | BPF_K, allow),
+            statement(BPF_LD | BPF_W | BPF_ABS, FIRST_ARG),
+        ]);
+        for family in [libc::AF_INET, libc::AF_INET6, libc::AF_PACKET] {
+            program.extend([
+                jump(BPF_JMP | BPF_JEQ | BPF_K, family as u32, 0, 1),
+                statement(BPF_RET | BPF_K, deny),
+            ]);
+        }
+        program.push(statement(BPF_RET | BPF_K, allow));
+        Ok(program)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub(super) fn network_filter() -> HalResult<Vec<libc::sock_filter>> {
+        Err(HalError::unsupported(
+            "network sandboxing on this architecture",
+        ))
+    }
+
+    /// Apply the ruleset and filter to the calling process; runs in the
+    /// child between fork and exec, so it must not allocate
+    pub(super) fn restrict_self(
+        ruleset: Option<RawFd>,
+        filter: Option<&[libc::sock_filter]>,
+    ) -> std::io::Result<()> {
+        if ruleset.is_none() && filter.is_none() {
+            return Ok(());
+        }
+        // SAFETY: plain prctl and syscall calls on values owned by the caller
+        unsafe {
+            // Required for an unprivileged process to restrict itself
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if let Some(fd) = ruleset {
+                if libc::syscall(libc::SYS_landlock_restrict_self, fd, 0u32) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(filter) = filter {
+                let program = libc::sock_fprog {
+                    len: filter.len() as libc::c_ushort,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                if libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn last_error(operation: &str, path: Option<&str>) -> HalError {
+        HalError::io_error(operation, path, std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::SandboxPolicy;
+    use crate::error::{HalError, HalResult};
+    use crate::process::JobObject;
+    use std::process::{Child, Command};
+
+    /// Start the command inside a Job Object carrying the policy's limits.
+    /// The process runs briefly before it is assigned to the job.
+    pub(super) fn spawn(
+        policy: &SandboxPolicy,
+        command: &mut Command,
+    ) -> HalResult<(Child, JobObject)> {
+        if policy.restricts_files() || policy.no_network {
+            return Err(HalError::unsupported(
+                "file system and network sandboxing on Windows",
+            ));
+        }
+        if policy.open_files.is_some() {
+            return Err(HalError::unsupported("open file limits on Windows"));
+        }
+        let job = JobObject::new()?;
+        job.limit(policy.cpu_time, policy.memory, policy.processes)?;
+        let mut child = command
+            .spawn()
+            .map_err(|e| HalError::io_error("spawn", None, e))?;
+        if let Err(e) = job.assign(&child) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok((child, job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn captures_output_and_exit_code() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let run = run(&SandboxPolicy::default(), command).unwrap();
+        assert_eq!(run.exit_code, 3);
+        assert_eq!(run.stdout, b"out\n");
+        assert_eq!(run.stderr, b"err\n");
+        assert!(!run.timed_out);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_commands_past_the_timeout() {
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        let policy = SandboxPolicy {
+            timeout: Some(Duration::from_millis(100)),
+            ..SandboxPolicy::default()
+        };
+        let run = run(&policy, command).unwrap();
+        assert!(run.timed_out);
+        assert_eq!(run.exit_code, 128 + 9);
+    }
+}
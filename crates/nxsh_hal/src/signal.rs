@@ -0,0 +1,424 @@
+//! Cross-platform signal delivery for the shell loop
+//!
+//! OS-level handlers never run shell code themselves: they only record the
+//! signal in a process-wide pending set. The shell polls [`take_pending`] at
+//! safe points (between commands, loop iterations, prompt redraws) and runs
+//! the matching trap there.
+//!
+//! Windows has no POSIX signals, so console control events are mapped onto
+//! their closest equivalents:
+//! - Ctrl-C → `SIGINT`
+//! - Ctrl-Break → `SIGQUIT`
+//! - console window closed → `SIGHUP`
+//! - logoff / shutdown → `SIGTERM`
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::error::HalResult;
+
+/// Signals the shell can trap, named after their POSIX counterparts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ShellSignal {
+    Hangup,
+    Interrupt,
+    Quit,
+    User1,
+    User2,
+    Pipe,
+    Alarm,
+    Terminate,
+    Child,
+    Continue,
+    TerminalStop,
+    TerminalInput,
+    TerminalOutput,
+    WindowChange,
+}
+
+impl ShellSignal {
+    /// Every trappable signal, in `trap -l` order
+    pub const ALL: [ShellSignal; 14] = [
+        ShellSignal::Hangup,
+        ShellSignal::Interrupt,
+        ShellSignal::Quit,
+        ShellSignal::User1,
+        ShellSignal::User2,
+        ShellSignal::Pipe,
+        ShellSignal::Alarm,
+        ShellSignal::Terminate,
+        ShellSignal::Child,
+        ShellSignal::Continue,
+        ShellSignal::TerminalStop,
+        ShellSignal::TerminalInput,
+        ShellSignal::TerminalOutput,
+        ShellSignal::WindowChange,
+    ];
+
+    /// Signal name without the `SIG` prefix (e.g. `INT`)
+    pub fn name(self) -> &'static str {
+        match self {
+            ShellSignal::Hangup => "HUP",
+            ShellSignal::Interrupt => "INT",
+            ShellSignal::Quit => "QUIT",
+            ShellSignal::User1 => "USR1",
+            ShellSignal::User2 => "USR2",
+            ShellSignal::Pipe => "PIPE",
+            ShellSignal::Alarm => "ALRM",
+            ShellSignal::Terminate => "TERM",
+            ShellSignal::Child => "CHLD",
+            ShellSignal::Continue => "CONT",
+            ShellSignal::TerminalStop => "TSTP",
+            ShellSignal::TerminalInput => "TTIN",
+            ShellSignal::TerminalOutput => "TTOU",
+            ShellSignal::WindowChange => "WINCH",
+        }
+    }
+
+    /// Platform signal number. Windows reports the Linux numbering so that
+    /// scripts using `trap ... 2` behave the same everywhere.
+    pub fn number(self) -> i32 {
+        #[cfg(unix)]
+        {
+            self.to_nix() as i32
+        }
+        #[cfg(not(unix))]
+        {
+            match self {
+                ShellSignal::Hangup => 1,
+                ShellSignal::Interrupt => 2,
+                ShellSignal::Quit => 3,
+                ShellSignal::User1 => 10,
+                ShellSignal::User2 => 12,
+                ShellSignal::Pipe => 13,
+                ShellSignal::Alarm => 14,
+                ShellSignal::Terminate => 15,
+                ShellSignal::Child => 17,
+                ShellSignal::Continue => 18,
+                ShellSignal::TerminalStop => 20,
+                ShellSignal::TerminalInput => 21,
+                ShellSignal::TerminalOutput => 22,
+                ShellSignal::WindowChange => 28,
+            }
+        }
+    }
+
+    /// Parse `INT`, `SIGINT` or `int`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.to_ascii_uppercase();
+        let bare = upper.strip_prefix("SIG").unwrap_or(&upper);
+        Self::ALL.into_iter().find(|sig| sig.name() == bare)
+    }
+
+    /// Look a signal up by its platform number
+    pub fn from_number(number: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|sig| sig.number() == number)
+    }
+
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+
+    #[cfg(unix)]
+    fn to_nix(self) -> nix::sys::signal::Signal {
+        use nix::sys::signal::Signal;
+        match self {
+            ShellSignal::Hangup => Signal::SIGHUP,
+            ShellSignal::Interrupt => Signal::SIGINT,
+            ShellSignal::Quit => Signal::SIGQUIT,
+            ShellSignal::User1 => Signal::SIGUSR1,
+            ShellSignal::User2 => Signal::SIGUSR2,
+            ShellSignal::Pipe => Signal::SIGPIPE,
+            ShellSignal::Alarm => Signal::SIGALRM,
+            ShellSignal::Terminate => Signal::SIGTERM,
+            ShellSignal::Child => Signal::SIGCHLD,
+            ShellSignal::Continue => Signal::SIGCONT,
+            ShellSignal::TerminalStop => Signal::SIGTSTP,
+            ShellSignal::TerminalInput => Signal::SIGTTIN,
+            ShellSignal::TerminalOutput => Signal::SIGTTOU,
+            ShellSignal::WindowChange => Signal::SIGWINCH,
+        }
+    }
+}
+
+impl std::fmt::Display for ShellSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("SIG{}", self.name()))
+    }
+}
+
+/// The Linux numbering of the signals `kill` can send, which Windows
+/// reports for the same reason as [`ShellSignal::number`]
+#[cfg(not(unix))]
+const LINUX_SIGNALS: [(i32, &str); 31] = [
+    (1, "HUP"),
+    (2, "INT"),
+    (3, "QUIT"),
+    (4, "ILL"),
+    (5, "TRAP"),
+    (6, "ABRT"),
+    (7, "BUS"),
+    (8, "FPE"),
+    (9, "KILL"),
+    (10, "USR1"),
+    (11, "SEGV"),
+    (12, "USR2"),
+    (13, "PIPE"),
+    (14, "ALRM"),
+    (15, "TERM"),
+    (16, "STKFLT"),
+    (17, "CHLD"),
+    (18, "CONT"),
+    (19, "STOP"),
+    (20, "TSTP"),
+    (21, "TTIN"),
+    (22, "TTOU"),
+    (23, "URG"),
+    (24, "XCPU"),
+    (25, "XFSZ"),
+    (26, "VTALRM"),
+    (27, "PROF"),
+    (28, "WINCH"),
+    (29, "IO"),
+    (30, "PWR"),
+    (31, "SYS"),
+];
+
+/// Every signal `kill` can send as `(number, name)`, in number order and
+/// named without the `SIG` prefix
+pub fn signal_table() -> Vec<(i32, &'static str)> {
+    #[cfg(unix)]
+    {
+        let mut table: Vec<(i32, &'static str)> = nix::sys::signal::Signal::iterator()
+            .map(|sig| (sig as i32, &sig.as_str()[3..]))
+            .collect();
+        table.sort_unstable();
+        table
+    }
+    #[cfg(not(unix))]
+    {
+        LINUX_SIGNALS.to_vec()
+    }
+}
+
+/// Read a signal given as a number, `KILL`, `SIGKILL` or `kill`. 0, which
+/// only checks that a process exists, is accepted as a number.
+pub fn parse_signal(spec: &str) -> Option<i32> {
+    let table = signal_table();
+    if let Ok(number) = spec.parse::<i32>() {
+        return (number == 0 || table.iter().any(|&(known, _)| known == number)).then_some(number);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let bare = match upper.strip_prefix("SIG").unwrap_or(&upper) {
+        "IOT" => "ABRT",
+        "CLD" => "CHLD",
+        "POLL" => "IO",
+        bare => bare,
+    };
+    table
+        .into_iter()
+        .find(|&(_, name)| name == bare)
+        .map(|(number, _)| number)
+}
+
+/// The name of signal `number` without the `SIG` prefix
+pub fn signal_name(number: i32) -> Option<&'static str> {
+    signal_table()
+        .into_iter()
+        .find(|&(known, _)| known == number)
+        .map(|(_, name)| name)
+}
+
+/// Signals received but not yet consumed by the shell loop
+static PENDING: AtomicU32 = AtomicU32::new(0);
+/// Signals whose handler records them into `PENDING`
+static CAUGHT: AtomicU32 = AtomicU32::new(0);
+/// Signals that are discarded on arrival
+static IGNORED: AtomicU32 = AtomicU32::new(0);
+
+/// Record `signal` as pending, exactly as if the OS had delivered it
+pub fn mark_pending(signal: ShellSignal) {
+    PENDING.fetch_or(signal.bit(), Ordering::SeqCst);
+}
+
+/// Drain the pending set, returning signals in `ShellSignal::ALL` order
+pub fn take_pending() -> Vec<ShellSignal> {
+    let bits = PENDING.swap(0, Ordering::SeqCst);
+    ShellSignal::ALL
+        .into_iter()
+        .filter(|sig| bits & sig.bit() != 0)
+        .collect()
+}
+
+/// Whether any signal is waiting to be handled
+pub fn has_pending() -> bool {
+    PENDING.load(Ordering::SeqCst) != 0
+}
+
+/// Start recording `signal` for delivery through [`take_pending`]
+pub fn catch(signal: ShellSignal) -> HalResult<()> {
+    platform::install(signal, Disposition::Catch)?;
+    IGNORED.fetch_and(!signal.bit(), Ordering::SeqCst);
+    CAUGHT.fetch_or(signal.bit(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Discard `signal` whenever it arrives
+pub fn ignore(signal: ShellSignal) -> HalResult<()> {
+    platform::install(signal, Disposition::Ignore)?;
+    CAUGHT.fetch_and(!signal.bit(), Ordering::SeqCst);
+    IGNORED.fetch_or(signal.bit(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Give `signal` back its platform default behaviour
+pub fn restore_default(signal: ShellSignal) -> HalResult<()> {
+    platform::install(signal, Disposition::Default)?;
+    CAUGHT.fetch_and(!signal.bit(), Ordering::SeqCst);
+    IGNORED.fetch_and(!signal.bit(), Ordering::SeqCst);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Disposition {
+    Catch,
+    Ignore,
+    Default,
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::{Disposition, ShellSignal};
+    use crate::error::{HalError, HalResult};
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet};
+    use std::os::raw::c_int;
+
+    extern "C" fn record(signo: c_int) {
+        // Only async-signal-safe work here: an atomic OR
+        if let Some(sig) = ShellSignal::from_number(signo) {
+            super::mark_pending(sig);
+        }
+    }
+
+    pub(super) fn install(signal: ShellSignal, disposition: Disposition) -> HalResult<()> {
+        let handler = match disposition {
+            Disposition::Catch => SigHandler::Handler(record),
+            Disposition::Ignore => SigHandler::SigIgn,
+            Disposition::Default => SigHandler::SigDfl,
+        };
+        let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+        // SAFETY: `record` only performs an atomic store, which is async-signal-safe
+        unsafe { sigaction(signal.to_nix(), &action) }.map_err(|e| {
+            HalError::invalid(&format!("failed to install handler for {signal}: {e}"))
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Disposition, ShellSignal, CAUGHT, IGNORED};
+    use crate::error::{HalError, HalResult};
+    use std::sync::atomic::Ordering;
+    use std::sync::OnceLock;
+    use windows_sys::Win32::Foundation::BOOL;
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT, CTRL_LOGOFF_EVENT,
+        CTRL_SHUTDOWN_EVENT,
+    };
+
+    static REGISTERED: OnceLock<bool> = OnceLock::new();
+
+    fn map_event(event: u32) -> Option<ShellSignal> {
+        match event {
+            CTRL_C_EVENT => Some(ShellSignal::Interrupt),
+            CTRL_BREAK_EVENT => Some(ShellSignal::Quit),
+            CTRL_CLOSE_EVENT => Some(ShellSignal::Hangup),
+            CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => Some(ShellSignal::Terminate),
+            _ => None,
+        }
+    }
+
+    unsafe extern "system" fn on_console_event(event: u32) -> BOOL {
+        let Some(sig) = map_event(event) else {
+            return 0;
+        };
+        if IGNORED.load(Ordering::SeqCst) & sig.bit() != 0 {
+            return 1;
+        }
+        if CAUGHT.load(Ordering::SeqCst) & sig.bit() != 0 {
+            super::mark_pending(sig);
+            return 1;
+        }
+        // Not handled: fall through to the next handler (default terminates)
+        0
+    }
+
+    pub(super) fn install(_signal: ShellSignal, _disposition: Disposition) -> HalResult<()> {
+        // One console handler serves every signal; dispositions live in the bitmasks
+        let ok = *REGISTERED.get_or_init(|| {
+            // SAFETY: registering a plain function pointer that only touches atomics
+            unsafe { SetConsoleCtrlHandler(Some(on_console_event), 1) != 0 }
+        });
+        if ok {
+            Ok(())
+        } else {
+            Err(HalError::invalid(
+                "failed to register console control handler",
+            ))
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::{Disposition, ShellSignal};
+    use crate::error::{HalError, HalResult};
+
+    pub(super) fn install(signal: ShellSignal, _disposition: Disposition) -> HalResult<()> {
+        Err(HalError::unsupported(&format!(
+            "signal handling for {signal} is not available on this platform"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for sig in ShellSignal::ALL {
+            assert_eq!(ShellSignal::from_name(sig.name()), Some(sig));
+            assert_eq!(ShellSignal::from_number(sig.number()), Some(sig));
+        }
+        assert_eq!(
+            ShellSignal::from_name("sigterm"),
+            Some(ShellSignal::Terminate)
+        );
+        assert_eq!(ShellSignal::from_name("KILL"), None);
+    }
+
+    #[test]
+    fn kill_signals_parse_by_name_and_number() {
+        assert_eq!(parse_signal("KILL"), Some(9));
+        assert_eq!(parse_signal("sigterm"), Some(15));
+        assert_eq!(parse_signal("9"), Some(9));
+        assert_eq!(parse_signal("0"), Some(0));
+        assert_eq!(parse_signal("BOGUS"), None);
+        assert_eq!(parse_signal("999"), None);
+        assert_eq!(signal_name(15), Some("TERM"));
+        assert_eq!(parse_signal("IOT"), parse_signal("ABRT"));
+        let table = signal_table();
+        assert!(table.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for sig in ShellSignal::ALL {
+            assert_eq!(parse_signal(sig.name()), Some(sig.number()));
+        }
+    }
+
+    #[test]
+    fn pending_set_drains_once() {
+        mark_pending(ShellSignal::User2);
+        assert!(take_pending().contains(&ShellSignal::User2));
+        assert!(!take_pending().contains(&ShellSignal::User2));
+    }
+}
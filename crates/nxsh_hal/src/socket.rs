@@ -0,0 +1,602 @@
+//! Open TCP and UDP sockets and the processes holding them
+//!
+//! [`list_sockets`] reads the kernel's tables in `/proc/net` on Linux,
+//! asks `lsof` on macOS and uses the IP helper tables on Windows. A socket
+//! held by a process of another user is listed without its owner unless
+//! the shell may inspect that process.
+
+use crate::error::{HalError, HalResult};
+use crate::process::ProcessId;
+use std::net::{IpAddr, SocketAddr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SocketProtocol {
+    Tcp,
+    Udp,
+}
+
+impl SocketProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            SocketProtocol::Tcp => "tcp",
+            SocketProtocol::Udp => "udp",
+        }
+    }
+}
+
+/// The TCP states, and those a UDP socket shows with and without a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SocketState {
+    Established,
+    SynSent,
+    SynReceived,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Closed,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    /// A UDP socket without a peer
+    Unconnected,
+    Unknown,
+}
+
+impl SocketState {
+    /// The name `netstat` and `ss` print
+    pub fn name(self) -> &'static str {
+        match self {
+            SocketState::Established => "ESTABLISHED",
+            SocketState::SynSent => "SYN_SENT",
+            SocketState::SynReceived => "SYN_RECV",
+            SocketState::FinWait1 => "FIN_WAIT1",
+            SocketState::FinWait2 => "FIN_WAIT2",
+            SocketState::TimeWait => "TIME_WAIT",
+            SocketState::Closed => "CLOSE",
+            SocketState::CloseWait => "CLOSE_WAIT",
+            SocketState::LastAck => "LAST_ACK",
+            SocketState::Listen => "LISTEN",
+            SocketState::Closing => "CLOSING",
+            SocketState::Unconnected => "UNCONN",
+            SocketState::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// One socket as read by [`list_sockets`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketEntry {
+    pub protocol: SocketProtocol,
+    pub local: SocketAddr,
+    /// The peer, or `None` for listening and unconnected sockets
+    pub remote: Option<SocketAddr>,
+    pub state: SocketState,
+    /// The owning process, when it can be seen
+    pub pid: Option<ProcessId>,
+    /// The owning process's name
+    pub program: Option<String>,
+}
+
+impl SocketEntry {
+    /// Whether it waits for anyone: a listening TCP socket or a UDP socket
+    /// without a peer
+    pub fn is_listening(&self) -> bool {
+        matches!(self.state, SocketState::Listen | SocketState::Unconnected)
+    }
+}
+
+/// Every TCP and UDP socket, ordered by protocol, local port and address
+pub fn list_sockets() -> HalResult<Vec<SocketEntry>> {
+    let mut sockets = read_sockets()?;
+    sockets.sort_by_key(|socket| {
+        (
+            socket.protocol,
+            socket.local.port(),
+            socket.local,
+            socket.remote,
+            socket.pid,
+        )
+    });
+    // A socket shared by several descriptors of one process shows once
+    sockets.dedup();
+    Ok(sockets)
+}
+
+/// The peer address of a socket, where the unspecified address and port 0
+/// mean it has none
+fn peer(address: SocketAddr) -> Option<SocketAddr> {
+    (!(address.ip().is_unspecified() && address.port() == 0)).then_some(address)
+}
+
+#[cfg(target_os = "linux")]
+fn read_sockets() -> HalResult<Vec<SocketEntry>> {
+    let owners = socket_owners();
+    let mut sockets = Vec::new();
+    for (file, protocol) in [
+        ("tcp", SocketProtocol::Tcp),
+        ("tcp6", SocketProtocol::Tcp),
+        ("udp", SocketProtocol::Udp),
+        ("udp6", SocketProtocol::Udp),
+    ] {
+        let path = format!("/proc/net/{file}");
+        let table = match std::fs::read_to_string(&path) {
+            Ok(table) => table,
+            // Kernels without IPv6 have no tcp6 and udp6 tables
+            Err(_) if file.ends_with('6') => continue,
+            Err(e) => return Err(HalError::io_error("read", Some(&path), e)),
+        };
+        for line in table.lines().skip(1) {
+            let Some((mut socket, inode)) = parse_proc_net_line(protocol, line) else {
+                continue;
+            };
+            if let Some((pid, program)) = owners.get(&inode) {
+                socket.pid = Some(*pid);
+                socket.program = Some(program.clone());
+            }
+            sockets.push(socket);
+        }
+    }
+    Ok(sockets)
+}
+
+/// Read one row of `/proc/net/tcp` and the like, returning the socket and
+/// its inode
+#[cfg(target_os = "linux")]
+fn parse_proc_net_line(protocol: SocketProtocol, line: &str) -> Option<(SocketEntry, u64)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let local = proc_net_address(fields.get(1)?)?;
+    let remote = proc_net_address(fields.get(2)?)?;
+    let code = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+    let inode = fields.get(9)?.parse().ok()?;
+    let state = match (protocol, code) {
+        // UDP sockets borrow TCP_CLOSE for having no peer
+        (SocketProtocol::Udp, 7) => SocketState::Unconnected,
+        (_, 1) => SocketState::Established,
+        (_, 2) => SocketState::SynSent,
+        (_, 3) => SocketState::SynReceived,
+        (_, 4) => SocketState::FinWait1,
+        (_, 5) => SocketState::FinWait2,
+        (_, 6) => SocketState::TimeWait,
+        (_, 7) => SocketState::Closed,
+        (_, 8) => SocketState::CloseWait,
+        (_, 9) => SocketState::LastAck,
+        (_, 10) => SocketState::Listen,
+        (_, 11) => SocketState::Closing,
+        _ => SocketState::Unknown,
+    };
+    let socket = SocketEntry {
+        protocol,
+        local,
+        remote: peer(remote),
+        state,
+        pid: None,
+        program: None,
+    };
+    Some((socket, inode))
+}
+
+/// Read `ADDRESS:PORT` from `/proc/net`, both in hex, the address as the
+/// kernel stores it: 32-bit words in host byte order
+#[cfg(target_os = "linux")]
+fn proc_net_address(text: &str) -> Option<SocketAddr> {
+    let (address, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for at in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(at..at + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// The process and its name for each socket inode, from the
+/// `socket:[INODE]` links in `/proc/PID/fd`, which can only be read for
+/// processes the shell may inspect
+#[cfg(target_os = "linux")]
+fn socket_owners() -> std::collections::HashMap<u64, (ProcessId, String)> {
+    let mut owners = std::collections::HashMap::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<ProcessId>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let mut program = None;
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            let Some(inode) = target.to_str().and_then(|target| {
+                target
+                    .strip_prefix("socket:[")?
+                    .strip_suffix(']')?
+                    .parse::<u64>()
+                    .ok()
+            }) else {
+                continue;
+            };
+            let program = program.get_or_insert_with(|| {
+                std::fs::read_to_string(entry.path().join("comm"))
+                    .map(|comm| comm.trim_end().to_string())
+                    .unwrap_or_default()
+            });
+            owners
+                .entry(inode)
+                .or_insert_with(|| (pid, program.clone()));
+        }
+    }
+    owners
+}
+
+#[cfg(target_os = "macos")]
+fn read_sockets() -> HalResult<Vec<SocketEntry>> {
+    // Without libc there is no proc_pidfdinfo, but lsof(8) reads the same
+    // data; it exits with 1 when it finds nothing, so only its output counts
+    let output = std::process::Command::new("/usr/sbin/lsof")
+        .args(["-nP", "-iTCP", "-iUDP", "-FpctPnT"])
+        .output()
+        .map_err(|e| HalError::io_error("spawn", Some("/usr/sbin/lsof"), e))?;
+    Ok(parse_lsof(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Read `lsof -F` output: a `p` line starts each process and an `f` line
+/// each of its files, and every other line is one field of the latest
+#[cfg(target_os = "macos")]
+fn parse_lsof(output: &str) -> Vec<SocketEntry> {
+    #[derive(Default)]
+    struct File<'a> {
+        ipv6: bool,
+        protocol: Option<SocketProtocol>,
+        name: &'a str,
+        state: &'a str,
+    }
+
+    fn finish(
+        file: Option<File>,
+        pid: Option<ProcessId>,
+        program: &Option<String>,
+    ) -> Option<SocketEntry> {
+        let file = file?;
+        let protocol = file.protocol?;
+        let (local, remote) = match file.name.split_once("->") {
+            Some((local, remote)) => (local, Some(lsof_address(remote, file.ipv6)?)),
+            None => (file.name, None),
+        };
+        let local = lsof_address(local, file.ipv6)?;
+        let remote = remote.and_then(peer);
+        let state = match (protocol, file.state) {
+            (SocketProtocol::Udp, _) if remote.is_some() => SocketState::Established,
+            (SocketProtocol::Udp, _) => SocketState::Unconnected,
+            (_, "ESTABLISHED") => SocketState::Established,
+            (_, "SYN_SENT") => SocketState::SynSent,
+            (_, "SYN_RECEIVED") => SocketState::SynReceived,
+            (_, "FIN_WAIT_1") => SocketState::FinWait1,
+            (_, "FIN_WAIT_2") => SocketState::FinWait2,
+            (_, "TIME_WAIT") => SocketState::TimeWait,
+            (_, "CLOSED") => SocketState::Closed,
+            (_, "CLOSE_WAIT") => SocketState::CloseWait,
+            (_, "LAST_ACK") => SocketState::LastAck,
+            (_, "LISTEN") => SocketState::Listen,
+            (_, "CLOSING") => SocketState::Closing,
+            _ => SocketState::Unknown,
+        };
+        Some(SocketEntry {
+            protocol,
+            local,
+            remote,
+            state,
+            pid,
+            program: program.clone(),
+        })
+    }
+
+    let mut sockets = Vec::new();
+    let mut pid = None;
+    let mut program = None;
+    let mut file: Option<File> = None;
+    for line in output.lines() {
+        let Some(kind) = line.chars().next() else {
+            continue;
+        };
+        let value = &line[kind.len_utf8()..];
+        match kind {
+            'p' | 'f' => {
+                sockets.extend(finish(file.take(), pid, &program));
+                if kind == 'p' {
+                    pid = value.parse().ok();
+                    program = None;
+                } else {
+                    file = Some(File::default());
+                }
+            }
+            'c' => program = Some(value.to_string()),
+            _ => {
+                let Some(file) = file.as_mut() else {
+                    continue;
+                };
+                match kind {
+                    't' => file.ipv6 = value == "IPv6",
+                    'P' if value == "TCP" => file.protocol = Some(SocketProtocol::Tcp),
+                    'P' if value == "UDP" => file.protocol = Some(SocketProtocol::Udp),
+                    'n' => file.name = value,
+                    'T' => {
+                        if let Some(state) = value.strip_prefix("ST=") {
+                            file.state = state;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    sockets.extend(finish(file, pid, &program));
+    sockets
+}
+
+/// Read an `lsof` address, `HOST:PORT` with `*` for any host or port and
+/// IPv6 hosts in brackets
+#[cfg(target_os = "macos")]
+fn lsof_address(text: &str, ipv6: bool) -> Option<SocketAddr> {
+    let (host, port) = text.rsplit_once(':')?;
+    let port = match port {
+        "*" => 0,
+        port => port.parse().ok()?,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    // A link-local address carries its interface after `%`
+    let host = host.split('%').next().unwrap_or(host);
+    let ip = match host {
+        "*" if ipv6 => IpAddr::from(std::net::Ipv6Addr::UNSPECIFIED),
+        "*" => IpAddr::from(std::net::Ipv4Addr::UNSPECIFIED),
+        host => host.parse().ok()?,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(windows)]
+fn read_sockets() -> HalResult<Vec<SocketEntry>> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetExtendedTcpTable, GetExtendedUdpTable, MIB_TCP6ROW_OWNER_PID, MIB_TCPROW_OWNER_PID,
+        MIB_UDP6ROW_OWNER_PID, MIB_UDPROW_OWNER_PID, TCP_TABLE_OWNER_PID_ALL, UDP_TABLE_OWNER_PID,
+    };
+    const AF_INET: u32 = 2;
+    const AF_INET6: u32 = 23;
+
+    let v4 = |address: u32, port: u32| {
+        SocketAddr::new(
+            IpAddr::from(Ipv4Addr::from(address.to_ne_bytes())),
+            windows_port(port),
+        )
+    };
+    let v6 = |address: [u8; 16], port: u32| {
+        SocketAddr::new(IpAddr::from(Ipv6Addr::from(address)), windows_port(port))
+    };
+    let mut sockets = Vec::new();
+    let mut push = |protocol, local, remote: Option<SocketAddr>, state, pid| {
+        sockets.push(SocketEntry {
+            protocol,
+            local,
+            remote: remote.and_then(peer),
+            state,
+            pid: Some(pid),
+            program: None,
+        })
+    };
+
+    // SAFETY: the closure passes on a buffer of the size it is told
+    let tcp = windows_table::<MIB_TCPROW_OWNER_PID>("GetExtendedTcpTable", |table, size| unsafe {
+        GetExtendedTcpTable(table, size, 0, AF_INET, TCP_TABLE_OWNER_PID_ALL, 0)
+    })?;
+    for row in tcp {
+        push(
+            SocketProtocol::Tcp,
+            v4(row.dwLocalAddr, row.dwLocalPort),
+            Some(v4(row.dwRemoteAddr, row.dwRemotePort)),
+            windows_tcp_state(row.dwState),
+            row.dwOwningPid,
+        );
+    }
+    // SAFETY: as above
+    let tcp6 =
+        windows_table::<MIB_TCP6ROW_OWNER_PID>("GetExtendedTcpTable", |table, size| unsafe {
+            GetExtendedTcpTable(table, size, 0, AF_INET6, TCP_TABLE_OWNER_PID_ALL, 0)
+        })?;
+    for row in tcp6 {
+        push(
+            SocketProtocol::Tcp,
+            v6(row.ucLocalAddr, row.dwLocalPort),
+            Some(v6(row.ucRemoteAddr, row.dwRemotePort)),
+            windows_tcp_state(row.dwState),
+            row.dwOwningPid,
+        );
+    }
+    // SAFETY: as above
+    let udp = windows_table::<MIB_UDPROW_OWNER_PID>("GetExtendedUdpTable", |table, size| unsafe {
+        GetExtendedUdpTable(table, size, 0, AF_INET, UDP_TABLE_OWNER_PID, 0)
+    })?;
+    for row in udp {
+        let local = v4(row.dwLocalAddr, row.dwLocalPort);
+        push(
+            SocketProtocol::Udp,
+            local,
+            None,
+            SocketState::Unconnected,
+            row.dwOwningPid,
+        );
+    }
+    // SAFETY: as above
+    let udp6 =
+        windows_table::<MIB_UDP6ROW_OWNER_PID>("GetExtendedUdpTable", |table, size| unsafe {
+            GetExtendedUdpTable(table, size, 0, AF_INET6, UDP_TABLE_OWNER_PID, 0)
+        })?;
+    for row in udp6 {
+        let local = v6(row.ucLocalAddr, row.dwLocalPort);
+        push(
+            SocketProtocol::Udp,
+            local,
+            None,
+            SocketState::Unconnected,
+            row.dwOwningPid,
+        );
+    }
+
+    let names: std::collections::HashMap<ProcessId, String> =
+        crate::process::ProcessSnapshot::capture()
+            .map(|snapshot| {
+                snapshot
+                    .into_processes()
+                    .into_iter()
+                    .map(|process| (process.pid, process.name))
+                    .collect()
+            })
+            .unwrap_or_default();
+    for socket in &mut sockets {
+        socket.program = socket.pid.and_then(|pid| names.get(&pid).cloned());
+    }
+    Ok(sockets)
+}
+
+/// Read one of the IP helper's tables, a row count followed by the rows,
+/// asking again while it grows between the calls
+#[cfg(windows)]
+fn windows_table<Row: Copy>(
+    function: &str,
+    read: impl Fn(*mut std::ffi::c_void, &mut u32) -> u32,
+) -> HalResult<Vec<Row>> {
+    use windows_sys::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+
+    // u64s keep the rows aligned
+    let mut buffer: Vec<u64> = Vec::new();
+    let mut size = 0u32;
+    loop {
+        match read(buffer.as_mut_ptr().cast(), &mut size) {
+            NO_ERROR => break,
+            ERROR_INSUFFICIENT_BUFFER => buffer = vec![0; (size as usize).div_ceil(8)],
+            code => {
+                return Err(HalError::network_error(
+                    function,
+                    None,
+                    None,
+                    &std::io::Error::from_raw_os_error(code as i32).to_string(),
+                ))
+            }
+        }
+    }
+    let offset = std::mem::size_of::<u32>().next_multiple_of(std::mem::align_of::<Row>());
+    let bytes = buffer.len() * 8;
+    if bytes < offset {
+        return Ok(Vec::new());
+    }
+    // SAFETY: the buffer holds at least the count, and rows are read only
+    // as far as it reaches
+    unsafe {
+        let count = buffer.as_ptr().cast::<u32>().read() as usize;
+        let count = count.min((bytes - offset) / std::mem::size_of::<Row>());
+        let rows = buffer.as_ptr().cast::<u8>().add(offset).cast::<Row>();
+        Ok(std::slice::from_raw_parts(rows, count).to_vec())
+    }
+}
+
+/// A port from the IP helper tables, in network byte order in the low half
+#[cfg(windows)]
+fn windows_port(port: u32) -> u16 {
+    u16::from_be(port as u16)
+}
+
+#[cfg(windows)]
+fn windows_tcp_state(state: u32) -> SocketState {
+    match state {
+        1 => SocketState::Closed,
+        2 => SocketState::Listen,
+        3 => SocketState::SynSent,
+        4 => SocketState::SynReceived,
+        5 => SocketState::Established,
+        6 => SocketState::FinWait1,
+        7 => SocketState::FinWait2,
+        8 => SocketState::CloseWait,
+        9 => SocketState::Closing,
+        10 => SocketState::LastAck,
+        11 => SocketState::TimeWait,
+        _ => SocketState::Unknown,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn read_sockets() -> HalResult<Vec<SocketEntry>> {
+    Err(HalError::unsupported(
+        "Socket listing is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lists_own_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let local = listener.local_addr().unwrap();
+        let sockets = list_sockets().unwrap();
+        let socket = sockets
+            .iter()
+            .find(|socket| socket.protocol == SocketProtocol::Tcp && socket.local == local)
+            .expect("listener listed");
+        assert_eq!(socket.state, SocketState::Listen);
+        assert_eq!(socket.remote, None);
+        assert!(socket.is_listening());
+        assert_eq!(socket.pid, Some(std::process::id()));
+    }
+
+    #[cfg(all(target_os = "linux", target_endian = "little"))]
+    #[test]
+    fn test_parse_proc_net_line() {
+        let line = "   0: 0100007F:1F90 0200007F:C350 01 00000000:00000000 00:00000000 \
+                    00000000  1000        0 31337 1 0000000000000000 20 4 30 10 -1";
+        let (socket, inode) = parse_proc_net_line(SocketProtocol::Tcp, line).unwrap();
+        assert_eq!(inode, 31337);
+        assert_eq!(socket.local, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(socket.remote, Some("127.0.0.2:50000".parse().unwrap()));
+        assert_eq!(socket.state, SocketState::Established);
+
+        let line = "  1: 00000000000000000000000001000000:0035 \
+                    00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 \
+                    00000000   101        0 4242 2 0000000000000000 0";
+        let (socket, _) = parse_proc_net_line(SocketProtocol::Udp, line).unwrap();
+        assert_eq!(socket.local, "[::1]:53".parse().unwrap());
+        assert_eq!(socket.remote, None);
+        assert_eq!(socket.state, SocketState::Unconnected);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_parse_lsof() {
+        let output = "p501\ncsshd\nf3\ntIPv6\nPTCP\nn*:22\nTST=LISTEN\nTQR=0\n\
+                      p777\ncmDNSResponder\nf4\ntIPv4\nPUDP\nn*:5353\n\
+                      f5\ntIPv4\nPTCP\nn10.0.0.2:50000->1.1.1.1:443\nTST=ESTABLISHED\n";
+        let sockets = parse_lsof(output);
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].local, "[::]:22".parse().unwrap());
+        assert_eq!(sockets[0].state, SocketState::Listen);
+        assert_eq!(sockets[0].program.as_deref(), Some("sshd"));
+        assert_eq!(sockets[1].state, SocketState::Unconnected);
+        assert_eq!(sockets[1].pid, Some(777));
+        assert_eq!(sockets[2].remote, Some("1.1.1.1:443".parse().unwrap()));
+        assert_eq!(sockets[2].state, SocketState::Established);
+    }
+}
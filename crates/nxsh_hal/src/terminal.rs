@@ -0,0 +1,112 @@
+//! The size of the controlling terminal
+//!
+//! [`size`] asks the terminal on standard output, or on standard input when
+//! output is redirected, for its columns and rows. Windows has no
+//! `SIGWINCH`, so [`ResizeWatch`] notices changes by asking again whenever
+//! it is polled; programs that pass the size on, such as a remote shell,
+//! poll it every so often.
+
+/// Columns and rows of the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+/// The terminal's size, or `None` when neither standard output nor standard
+/// input is a terminal
+pub fn size() -> Option<TerminalSize> {
+    platform::size().filter(|size| size.columns > 0 && size.rows > 0)
+}
+
+/// Tells when the terminal's size has changed since it was last asked
+#[derive(Debug)]
+pub struct ResizeWatch {
+    last: Option<TerminalSize>,
+}
+
+impl ResizeWatch {
+    /// Start watching from the current size
+    pub fn new() -> Self {
+        ResizeWatch { last: size() }
+    }
+
+    /// The size the watch started from or last reported
+    pub fn current(&self) -> Option<TerminalSize> {
+        self.last
+    }
+
+    /// The new size when it differs from the last one reported
+    pub fn changed(&mut self) -> Option<TerminalSize> {
+        let now = size()?;
+        if self.last == Some(now) {
+            return None;
+        }
+        self.last = Some(now);
+        Some(now)
+    }
+}
+
+impl Default for ResizeWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::TerminalSize;
+    use nix::libc;
+
+    pub(super) fn size() -> Option<TerminalSize> {
+        [libc::STDOUT_FILENO, libc::STDIN_FILENO, libc::STDERR_FILENO]
+            .into_iter()
+            .find_map(|fd| {
+                let mut winsize = libc::winsize {
+                    ws_row: 0,
+                    ws_col: 0,
+                    ws_xpixel: 0,
+                    ws_ypixel: 0,
+                };
+                // SAFETY: TIOCGWINSZ only fills in the winsize it is given
+                let ok = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsize) } == 0;
+                ok.then_some(TerminalSize {
+                    columns: winsize.ws_col,
+                    rows: winsize.ws_row,
+                })
+            })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::TerminalSize;
+    use windows_sys::Win32::System::Console::{
+        GetConsoleScreenBufferInfo, GetStdHandle, CONSOLE_SCREEN_BUFFER_INFO, STD_ERROR_HANDLE,
+        STD_OUTPUT_HANDLE,
+    };
+
+    pub(super) fn size() -> Option<TerminalSize> {
+        [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE]
+            .into_iter()
+            .find_map(|which| {
+                // SAFETY: the buffer info is plain data filled in by the call
+                let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { std::mem::zeroed() };
+                let ok = unsafe { GetConsoleScreenBufferInfo(GetStdHandle(which), &mut info) } != 0;
+                let window = info.srWindow;
+                ok.then(|| TerminalSize {
+                    columns: (window.Right - window.Left + 1).max(0) as u16,
+                    rows: (window.Bottom - window.Top + 1).max(0) as u16,
+                })
+            })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::TerminalSize;
+
+    pub(super) fn size() -> Option<TerminalSize> {
+        None
+    }
+}
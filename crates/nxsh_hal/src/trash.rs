@@ -0,0 +1,961 @@
+//! The desktop trash: moving files into it, listing, restoring and
+//! emptying it
+//!
+//! On Linux and the BSDs this is the freedesktop.org trash: a file goes to
+//! `$XDG_DATA_HOME/Trash` when it is on the same file system as that
+//! directory, or else to `.Trash/$UID` or `.Trash-$UID` at the top of its
+//! own file system, with a `.trashinfo` file recording where it came from
+//! and when. macOS keeps no such record the system would share, so files
+//! are moved to `~/.Trash`, or `.Trashes/$UID` on other volumes, with the
+//! original path kept in extended attributes. On Windows files go to the
+//! Recycle Bin through `IFileOperation`, and its `$I` index files are read
+//! back for listing and restoring.
+
+use crate::error::{HalError, HalResult};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A file or directory in the trash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedItem {
+    /// Its name in the trash, unique within the trash directory
+    pub id: String,
+    /// Where it was, when that was recorded
+    pub original_path: Option<PathBuf>,
+    pub deleted: Option<SystemTime>,
+    /// Where it is now
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// The file recording where it came from, removed along with it
+    record: Option<PathBuf>,
+}
+
+/// Move `path` to the trash; a symbolic link is moved, not its target
+pub fn trash<P: AsRef<Path>>(path: P) -> HalResult<()> {
+    let path = path.as_ref();
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+    {
+        xdg::put(path, &xdg::home_trash()?).map(|_| ())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::put(path).map(|_| ())
+    }
+    #[cfg(windows)]
+    {
+        windows::put(path)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Err(HalError::unsupported("trash"))
+    }
+}
+
+/// Everything in the trash, oldest first
+pub fn list_trash() -> HalResult<Vec<TrashedItem>> {
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+    let mut items = xdg::list(&xdg::trash_dirs(&xdg::home_trash()?));
+    #[cfg(target_os = "macos")]
+    let mut items = macos::list();
+    #[cfg(windows)]
+    let mut items = windows::list()?;
+    #[cfg(not(any(unix, windows)))]
+    let mut items: Vec<TrashedItem> = return Err(HalError::unsupported("trash"));
+
+    items.sort_by(|a, b| a.deleted.cmp(&b.deleted).then_with(|| a.id.cmp(&b.id)));
+    Ok(items)
+}
+
+/// Put `item` back where it was, returning that path. Fails when its
+/// original path is unknown or something else is there now.
+pub fn restore_trashed(item: &TrashedItem) -> HalResult<PathBuf> {
+    let Some(original) = &item.original_path else {
+        return Err(HalError::invalid("its original location is not known"));
+    };
+    let shown = original.display().to_string();
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(HalError::io_error(
+            "restore",
+            Some(&shown),
+            io::Error::new(io::ErrorKind::AlreadyExists, "File exists"),
+        ));
+    }
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| HalError::io_error("create_dir_all", Some(&shown), e))?;
+    }
+    fs::rename(&item.path, original).map_err(|e| HalError::io_error("rename", Some(&shown), e))?;
+    forget(item)?;
+    Ok(original.clone())
+}
+
+/// Delete `item` from the trash for good
+pub fn purge_trashed(item: &TrashedItem) -> HalResult<()> {
+    let shown = item.path.display().to_string();
+    let removed = if item.is_dir {
+        fs::remove_dir_all(&item.path)
+    } else {
+        fs::remove_file(&item.path)
+    };
+    match removed {
+        Ok(()) => {}
+        // Already gone, only the record is left
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(HalError::io_error("remove", Some(&shown), e)),
+    }
+    forget(item)
+}
+
+/// Delete everything in the trash for good, returning how many items
+/// there were
+pub fn empty_trash() -> HalResult<usize> {
+    let items = list_trash()?;
+    #[cfg(windows)]
+    {
+        windows::empty()?;
+        Ok(items.len())
+    }
+    #[cfg(not(windows))]
+    {
+        for item in &items {
+            purge_trashed(item)?;
+        }
+        Ok(items.len())
+    }
+}
+
+/// Remove the record of an item that left the trash
+fn forget(item: &TrashedItem) -> HalResult<()> {
+    match &item.record {
+        Some(record) => match fs::remove_file(record) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(HalError::io_error(
+                "remove",
+                Some(&record.display().to_string()),
+                e,
+            )),
+        },
+        None => Ok(()),
+    }
+}
+
+/// `path` made absolute through its parent, keeping a final symbolic link
+#[cfg(unix)]
+fn absolute(path: &Path) -> HalResult<PathBuf> {
+    let shown = path.display().to_string();
+    let name = path
+        .file_name()
+        .filter(|name| *name != "." && *name != "..")
+        .ok_or_else(|| HalError::invalid("cannot be moved to the trash"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let parent = parent
+        .canonicalize()
+        .map_err(|e| HalError::io_error("canonicalize", Some(&shown), e))?;
+    Ok(parent.join(name))
+}
+
+/// Create `dir` and any missing parents readable only by the user
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> HalResult<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| HalError::io_error("mkdir", Some(&dir.display().to_string()), e))
+}
+
+/// The trash directories of the volumes other than the home one, for
+/// the current user: `{top}/{name}` with `name` relative to each mount
+#[cfg(unix)]
+fn volume_trashes(names: &[String]) -> Vec<(PathBuf, PathBuf)> {
+    let Ok(mounts) = crate::mounts::list_mounts() else {
+        return Vec::new();
+    };
+    let mut dirs = Vec::new();
+    for mount in mounts {
+        for name in names {
+            let dir = mount.mount_point.join(name);
+            if dir.is_dir() {
+                dirs.push((dir, mount.mount_point.clone()));
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+    nix::unistd::getuid().as_raw()
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+mod xdg {
+    use super::{absolute, private_dir, uid, volume_trashes, TrashedItem};
+    use crate::error::{HalError, HalResult};
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    /// A trash directory and the top of the volume it is on, which the
+    /// paths it records are relative to; `None` for the home trash
+    pub(super) struct TrashDir {
+        pub(super) path: PathBuf,
+        pub(super) topdir: Option<PathBuf>,
+    }
+
+    /// `$XDG_DATA_HOME/Trash`, by default `~/.local/share/Trash`
+    pub(super) fn home_trash() -> HalResult<PathBuf> {
+        let data = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+            _ => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".local/share"),
+                None => return Err(HalError::invalid("HOME is not set")),
+            },
+        };
+        Ok(data.join("Trash"))
+    }
+
+    /// The home trash and the volume trashes that exist
+    pub(super) fn trash_dirs(home: &Path) -> Vec<TrashDir> {
+        let mut dirs = vec![TrashDir {
+            path: home.to_path_buf(),
+            topdir: None,
+        }];
+        let uid = uid();
+        let names = [format!(".Trash/{uid}"), format!(".Trash-{uid}")];
+        let mut seen = vec![home.canonicalize().unwrap_or_else(|_| home.to_path_buf())];
+        for (path, topdir) in volume_trashes(&names) {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if !seen.contains(&canonical) {
+                seen.push(canonical);
+                dirs.push(TrashDir {
+                    path,
+                    topdir: Some(topdir),
+                });
+            }
+        }
+        dirs
+    }
+
+    /// The trash `path` goes to: the home one when on its file system,
+    /// otherwise `$topdir/.Trash/$uid` when the administrator made a
+    /// sticky `$topdir/.Trash`, or else `$topdir/.Trash-$uid`
+    fn trash_for(path: &Path, device: u64, home: &Path) -> HalResult<TrashDir> {
+        private_dir(home)?;
+        let home_device = fs::metadata(home)
+            .map_err(|e| HalError::io_error("stat", Some(&home.display().to_string()), e))?
+            .dev();
+        if home_device == device {
+            return Ok(TrashDir {
+                path: home.to_path_buf(),
+                topdir: None,
+            });
+        }
+
+        let topdir = crate::mounts::mount_containing(path.parent().unwrap_or(path))?
+            .map(|mount| mount.mount_point)
+            .ok_or_else(|| HalError::invalid("no mounted file system holds it"))?;
+        let uid = uid();
+        let admin = topdir.join(".Trash");
+        let shared = fs::symlink_metadata(&admin)
+            .is_ok_and(|meta| meta.is_dir() && meta.mode() & 0o1000 != 0);
+        let path = if shared {
+            admin.join(uid.to_string())
+        } else {
+            topdir.join(format!(".Trash-{uid}"))
+        };
+        private_dir(&path)?;
+        Ok(TrashDir {
+            path,
+            topdir: Some(topdir),
+        })
+    }
+
+    pub(super) fn put(path: &Path, home: &Path) -> HalResult<TrashedItem> {
+        let absolute = absolute(path)?;
+        let shown = path.display().to_string();
+        let meta = fs::symlink_metadata(&absolute)
+            .map_err(|e| HalError::io_error("stat", Some(&shown), e))?;
+        let trash = trash_for(&absolute, meta.dev(), home)?;
+        if absolute.starts_with(&trash.path) {
+            return Err(HalError::invalid("already in the trash"));
+        }
+        let files = trash.path.join("files");
+        let info = trash.path.join("info");
+        private_dir(&files)?;
+        private_dir(&info)?;
+
+        // Paths on other volumes are recorded relative to their top
+        let recorded = match &trash.topdir {
+            Some(topdir) => absolute.strip_prefix(topdir).unwrap_or(&absolute),
+            None => &absolute,
+        };
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode(recorded.as_os_str().as_bytes()),
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+
+        // The info file is made first and exclusively, which claims the name
+        let name = absolute.file_name().unwrap_or(OsStr::new("file"));
+        let mut n = 1;
+        let (id, record) = loop {
+            let id = if n == 1 {
+                name.to_string_lossy().into_owned()
+            } else {
+                format!("{}.{n}", name.to_string_lossy())
+            };
+            n += 1;
+            if fs::symlink_metadata(files.join(&id)).is_ok() {
+                continue;
+            }
+            let record = info.join(format!("{id}.trashinfo"));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&record)
+            {
+                Ok(mut file) => {
+                    file.write_all(contents.as_bytes()).map_err(|e| {
+                        HalError::io_error("write", Some(&record.display().to_string()), e)
+                    })?;
+                    break (id, record);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(HalError::io_error(
+                        "create",
+                        Some(&record.display().to_string()),
+                        e,
+                    ))
+                }
+            }
+        };
+
+        let target = files.join(&id);
+        if let Err(e) = fs::rename(&absolute, &target) {
+            let _ = fs::remove_file(&record);
+            return Err(HalError::io_error("rename", Some(&shown), e));
+        }
+        Ok(TrashedItem {
+            id,
+            original_path: Some(absolute),
+            deleted: Some(SystemTime::now()),
+            path: target,
+            is_dir: meta.is_dir(),
+            record: Some(record),
+        })
+    }
+
+    pub(super) fn list(dirs: &[TrashDir]) -> Vec<TrashedItem> {
+        let mut items = Vec::new();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(dir.path.join("info")) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let record = entry.path();
+                let file_name = entry.file_name();
+                let Some(id) = file_name
+                    .to_str()
+                    .and_then(|n| n.strip_suffix(".trashinfo"))
+                else {
+                    continue;
+                };
+                let path = dir.path.join("files").join(id);
+                // A record whose file is gone is left for the spec's cleanup
+                let Ok(meta) = fs::symlink_metadata(&path) else {
+                    continue;
+                };
+                let Ok(text) = fs::read_to_string(&record) else {
+                    continue;
+                };
+                let (original, deleted) = parse_info(&text);
+                let original_path = original.map(|original| match &dir.topdir {
+                    Some(topdir) if original.is_relative() => topdir.join(original),
+                    _ => original,
+                });
+                items.push(TrashedItem {
+                    id: id.to_string(),
+                    original_path,
+                    deleted,
+                    path,
+                    is_dir: meta.is_dir(),
+                    record: Some(record),
+                });
+            }
+        }
+        items
+    }
+
+    /// The `Path` and `DeletionDate` of a `.trashinfo` file
+    pub(super) fn parse_info(text: &str) -> (Option<PathBuf>, Option<SystemTime>) {
+        let mut original = None;
+        let mut deleted = None;
+        let mut in_section = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_section = line == "[Trash Info]";
+            } else if !in_section {
+                continue;
+            } else if let Some(value) = line.strip_prefix("Path=") {
+                original = Some(PathBuf::from(std::ffi::OsString::from_vec(decode(value))));
+            } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+                use chrono::TimeZone;
+                deleted = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+                    .ok()
+                    .and_then(|naive| chrono::Local.from_local_datetime(&naive).earliest())
+                    .map(SystemTime::from);
+            }
+        }
+        (original, deleted)
+    }
+
+    /// Percent-encode the bytes of a path the way URIs are
+    pub(super) fn encode(bytes: &[u8]) -> String {
+        let mut text = String::with_capacity(bytes.len());
+        for &byte in bytes {
+            if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+                text.push(byte as char);
+            } else {
+                text.push_str(&format!("%{byte:02X}"));
+            }
+        }
+        text
+    }
+
+    pub(super) fn decode(text: &str) -> Vec<u8> {
+        let bytes = text.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[i], hex) {
+                (b'%', Some(byte)) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        decoded
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::{absolute, private_dir, uid, volume_trashes, TrashedItem};
+    use crate::error::{HalError, HalResult};
+    use crate::fs::{get_xattr, set_xattr};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Where a file came from and when it was trashed, kept on the file
+    const ORIGIN: &str = "org.nexusshell.trash.origin";
+    const DELETED: &str = "org.nexusshell.trash.deleted";
+
+    fn home_trash() -> HalResult<PathBuf> {
+        match std::env::var_os("HOME") {
+            Some(home) => Ok(Path::new(&home).join(".Trash")),
+            None => Err(HalError::invalid("HOME is not set")),
+        }
+    }
+
+    pub(super) fn put(path: &Path) -> HalResult<TrashedItem> {
+        let absolute = absolute(path)?;
+        let shown = path.display().to_string();
+        let meta = fs::symlink_metadata(&absolute)
+            .map_err(|e| HalError::io_error("stat", Some(&shown), e))?;
+        let home = home_trash()?;
+        private_dir(&home)?;
+        let home_device = fs::metadata(&home)
+            .map_err(|e| HalError::io_error("stat", Some(&home.display().to_string()), e))?
+            .dev();
+        let trash = if home_device == meta.dev() {
+            home
+        } else {
+            let topdir = crate::mounts::mount_containing(absolute.parent().unwrap_or(&absolute))?
+                .map(|mount| mount.mount_point)
+                .ok_or_else(|| HalError::invalid("no mounted file system holds it"))?;
+            let dir = topdir.join(".Trashes").join(uid().to_string());
+            private_dir(&dir)?;
+            dir
+        };
+        if absolute.starts_with(&trash) {
+            return Err(HalError::invalid("already in the trash"));
+        }
+
+        // Finder's way of telling apart files of the same name
+        let name = absolute.file_name().unwrap_or_default().to_string_lossy();
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => (&name[..dot], &name[dot..]),
+            _ => (&name[..], ""),
+        };
+        let mut n = 1;
+        let target = loop {
+            let id = if n == 1 {
+                name.to_string()
+            } else {
+                format!("{stem} {n}{extension}")
+            };
+            n += 1;
+            let target = trash.join(&id);
+            if fs::symlink_metadata(&target).is_err() {
+                break target;
+            }
+        };
+        fs::rename(&absolute, &target)
+            .map_err(|e| HalError::io_error("rename", Some(&shown), e))?;
+        let now = SystemTime::now();
+        let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        // Without the attributes the file is still trashed, just not restorable
+        let _ = set_xattr(&target, ORIGIN, absolute.as_os_str().as_bytes(), false);
+        let _ = set_xattr(&target, DELETED, seconds.to_string().as_bytes(), false);
+        Ok(TrashedItem {
+            id: target
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            original_path: Some(absolute),
+            deleted: Some(now),
+            path: target,
+            is_dir: meta.is_dir(),
+            record: None,
+        })
+    }
+
+    pub(super) fn list() -> Vec<TrashedItem> {
+        let mut dirs = Vec::new();
+        if let Ok(home) = home_trash() {
+            dirs.push(home);
+        }
+        let names = [format!(".Trashes/{}", uid())];
+        dirs.extend(volume_trashes(&names).into_iter().map(|(dir, _)| dir));
+
+        let mut items = Vec::new();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let id = entry.file_name().to_string_lossy().into_owned();
+                if id == ".DS_Store" {
+                    continue;
+                }
+                let path = entry.path();
+                let Ok(meta) = fs::symlink_metadata(&path) else {
+                    continue;
+                };
+                let original_path = get_xattr(&path, ORIGIN, false)
+                    .ok()
+                    .flatten()
+                    .map(|bytes| PathBuf::from(OsString::from_vec(bytes)));
+                let deleted = get_xattr(&path, DELETED, false)
+                    .ok()
+                    .flatten()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .and_then(|text| text.parse().ok())
+                    .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+                items.push(TrashedItem {
+                    id,
+                    original_path,
+                    deleted,
+                    path,
+                    is_dir: meta.is_dir(),
+                    record: None,
+                });
+            }
+        }
+        items
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::TrashedItem;
+    use crate::error::{HalError, HalResult};
+    use std::ffi::c_void;
+    use std::fs;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use windows_sys::core::{GUID, HRESULT};
+    use windows_sys::Win32::Foundation::BOOL;
+
+    const CLSID_FILE_OPERATION: GUID = GUID::from_u128(0x3ad05575_8857_4850_9277_11b85bdb8e09);
+    const IID_FILE_OPERATION: GUID = GUID::from_u128(0x947aab5f_0a5c_4c13_b4d6_4bf7836fc9f8);
+    const IID_SHELL_ITEM: GUID = GUID::from_u128(0x43826d1e_e718_42ee_bc55_a1e261c37bfe);
+
+    const FOF_SILENT: u32 = 0x0004;
+    const FOF_NOCONFIRMATION: u32 = 0x0010;
+    const FOF_ALLOWUNDO: u32 = 0x0040;
+    const FOF_NOERRORUI: u32 = 0x0400;
+    const FOFX_EARLYFAILURE: u32 = 0x0010_0000;
+    const FOFX_RECYCLEONDELETE: u32 = 0x0008_0000;
+
+    /// The start of every COM object: its table of methods
+    #[repr(C)]
+    struct Object<V> {
+        vtable: *const V,
+    }
+
+    #[repr(C)]
+    struct UnknownVtbl {
+        query_interface: usize,
+        add_ref: usize,
+        release: unsafe extern "system" fn(*mut c_void) -> u32,
+    }
+
+    /// `IFileOperation`, with the methods not called here left untyped
+    #[repr(C)]
+    struct FileOperationVtbl {
+        unknown: UnknownVtbl,
+        advise: usize,
+        unadvise: usize,
+        set_operation_flags: unsafe extern "system" fn(*mut c_void, u32) -> HRESULT,
+        set_progress_message: usize,
+        set_progress_dialog: usize,
+        set_properties: usize,
+        set_owner_window: usize,
+        apply_properties_to_item: usize,
+        apply_properties_to_items: usize,
+        rename_item: usize,
+        rename_items: usize,
+        move_item: usize,
+        move_items: usize,
+        copy_item: usize,
+        copy_items: usize,
+        delete_item: unsafe extern "system" fn(*mut c_void, *mut c_void, *mut c_void) -> HRESULT,
+        delete_items: usize,
+        new_item: usize,
+        perform_operations: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+        get_any_operations_aborted: unsafe extern "system" fn(*mut c_void, *mut BOOL) -> HRESULT,
+    }
+
+    /// A COM reference released when dropped
+    struct Com(*mut c_void);
+
+    impl Drop for Com {
+        fn drop(&mut self) {
+            // SAFETY: every COM vtable starts with IUnknown's
+            unsafe {
+                let object = self.0 as *mut Object<UnknownVtbl>;
+                ((*(*object).vtable).release)(self.0);
+            }
+        }
+    }
+
+    fn check(call: &str, path: &Path, hr: HRESULT) -> HalResult<()> {
+        if hr < 0 {
+            Err(HalError::io_error(
+                call,
+                Some(&path.display().to_string()),
+                io::Error::from_raw_os_error(hr),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(super) fn put(path: &Path) -> HalResult<()> {
+        use windows_sys::Win32::System::Com::{
+            CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_APARTMENTTHREADED,
+            COINIT_DISABLE_OLE1DDE,
+        };
+        use windows_sys::Win32::UI::Shell::SHCreateItemFromParsingName;
+
+        let absolute = std::path::absolute(path)
+            .map_err(|e| HalError::io_error("absolute", Some(&path.display().to_string()), e))?;
+        let wide: Vec<u16> = absolute.as_os_str().encode_wide().chain(Some(0)).collect();
+        // SAFETY: COM is set up for this thread and torn down below if it
+        // was this call that set it up
+        let initialized = unsafe {
+            CoInitializeEx(
+                std::ptr::null(),
+                COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE,
+            )
+        };
+        let result = (|| {
+            let mut operation = std::ptr::null_mut();
+            // SAFETY: the out pointer receives an IFileOperation
+            check("CoCreateInstance", path, unsafe {
+                CoCreateInstance(
+                    &CLSID_FILE_OPERATION,
+                    std::ptr::null_mut(),
+                    CLSCTX_ALL,
+                    &IID_FILE_OPERATION,
+                    &mut operation,
+                )
+            })?;
+            let operation = Com(operation);
+            let mut item = std::ptr::null_mut();
+            // SAFETY: `wide` is nul terminated; the out pointer receives an
+            // IShellItem
+            check("SHCreateItemFromParsingName", path, unsafe {
+                SHCreateItemFromParsingName(
+                    wide.as_ptr(),
+                    std::ptr::null_mut(),
+                    &IID_SHELL_ITEM,
+                    &mut item,
+                )
+            })?;
+            let item = Com(item);
+            // SAFETY: `operation` is a live IFileOperation
+            let vtable = unsafe { &*(*(operation.0 as *mut Object<FileOperationVtbl>)).vtable };
+            let flags = FOF_SILENT
+                | FOF_NOCONFIRMATION
+                | FOF_ALLOWUNDO
+                | FOF_NOERRORUI
+                | FOFX_EARLYFAILURE
+                | FOFX_RECYCLEONDELETE;
+            unsafe {
+                check(
+                    "SetOperationFlags",
+                    path,
+                    (vtable.set_operation_flags)(operation.0, flags),
+                )?;
+                check(
+                    "DeleteItem",
+                    path,
+                    (vtable.delete_item)(operation.0, item.0, std::ptr::null_mut()),
+                )?;
+                check(
+                    "PerformOperations",
+                    path,
+                    (vtable.perform_operations)(operation.0),
+                )?;
+                let mut aborted: BOOL = 0;
+                check(
+                    "GetAnyOperationsAborted",
+                    path,
+                    (vtable.get_any_operations_aborted)(operation.0, &mut aborted),
+                )?;
+                if aborted != 0 {
+                    return Err(HalError::io_error(
+                        "PerformOperations",
+                        Some(&path.display().to_string()),
+                        io::Error::new(io::ErrorKind::Interrupted, "the operation was cancelled"),
+                    ));
+                }
+            }
+            Ok(())
+        })();
+        if initialized >= 0 {
+            // SAFETY: balances the successful CoInitializeEx above
+            unsafe { CoUninitialize() };
+        }
+        result
+    }
+
+    /// The string SID of the user running the shell, which names the
+    /// user's folder in each `$Recycle.Bin`
+    fn user_sid() -> HalResult<String> {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::Security::{
+            GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER,
+        };
+        use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+        let mut token = 0;
+        // SAFETY: `token` receives the token handle
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return Err(HalError::io_error(
+                "OpenProcessToken",
+                None,
+                io::Error::last_os_error(),
+            ));
+        }
+        // A TOKEN_USER followed by the SID it points to; u64s keep it aligned
+        let mut buffer = [0u64; 64];
+        let mut needed = 0u32;
+        // SAFETY: the buffer is as large as the length passed
+        let read = unsafe {
+            GetTokenInformation(
+                token,
+                TokenUser,
+                buffer.as_mut_ptr().cast(),
+                std::mem::size_of_val(&buffer) as u32,
+                &mut needed,
+            )
+        };
+        let error = io::Error::last_os_error();
+        // SAFETY: the token handle is owned here and closed once
+        unsafe { CloseHandle(token) };
+        if read == 0 {
+            return Err(HalError::io_error("GetTokenInformation", None, error));
+        }
+        // SAFETY: GetTokenInformation filled the buffer with a TOKEN_USER
+        // whose SID lies within it
+        Ok(unsafe {
+            let sid = (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid;
+            crate::fs::windows_acl::sid_string(sid as *const u8)
+        })
+    }
+
+    /// A `$I` index file: format version, size, deletion time as a
+    /// FILETIME, then the original path, in a fixed 260 character field in
+    /// version 1 and after its length in version 2
+    pub(super) fn parse_index(bytes: &[u8]) -> Option<(SystemTime, PathBuf)> {
+        let word = |at: usize| {
+            bytes
+                .get(at..at + 8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        };
+        let version = word(0)?;
+        let filetime = word(16)?;
+        let name = match version {
+            1 => bytes.get(24..24 + 520)?,
+            2 => {
+                let chars = u32::from_le_bytes(bytes.get(24..28)?.try_into().unwrap()) as usize;
+                bytes.get(28..28 + chars * 2)?
+            }
+            _ => return None,
+        };
+        let units: Vec<u16> = name
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        // FILETIMEs count 100ns from 1601
+        let since_unix = filetime.checked_sub(116_444_736_000_000_000)?;
+        let deleted = UNIX_EPOCH + Duration::from_nanos(since_unix.saturating_mul(100));
+        Some((deleted, PathBuf::from(String::from_utf16_lossy(&units))))
+    }
+
+    pub(super) fn list() -> HalResult<Vec<TrashedItem>> {
+        let sid = user_sid()?;
+        let mut items = Vec::new();
+        for mount in crate::mounts::list_mounts()? {
+            let bin = mount.mount_point.join("$Recycle.Bin").join(&sid);
+            let Ok(entries) = fs::read_dir(&bin) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let Some(suffix) = name.strip_prefix("$I") else {
+                    continue;
+                };
+                let path = bin.join(format!("$R{suffix}"));
+                let Ok(meta) = fs::symlink_metadata(&path) else {
+                    continue;
+                };
+                let Some((deleted, original)) =
+                    fs::read(entry.path()).ok().and_then(|b| parse_index(&b))
+                else {
+                    continue;
+                };
+                items.push(TrashedItem {
+                    id: suffix.to_string(),
+                    original_path: Some(original),
+                    deleted: Some(deleted),
+                    path,
+                    is_dir: meta.is_dir(),
+                    record: Some(entry.path()),
+                });
+            }
+        }
+        Ok(items)
+    }
+
+    pub(super) fn empty() -> HalResult<()> {
+        use windows_sys::Win32::UI::Shell::{
+            SHEmptyRecycleBinW, SHERB_NOCONFIRMATION, SHERB_NOPROGRESSUI, SHERB_NOSOUND,
+        };
+        // SAFETY: no window and every drive's bin
+        let hr = unsafe {
+            SHEmptyRecycleBinW(
+                0,
+                std::ptr::null(),
+                SHERB_NOCONFIRMATION | SHERB_NOPROGRESSUI | SHERB_NOSOUND,
+            )
+        };
+        // An empty bin reports E_UNEXPECTED
+        if hr < 0 && hr != 0x8000_FFFFu32 as i32 {
+            return Err(HalError::io_error(
+                "SHEmptyRecycleBinW",
+                None,
+                io::Error::from_raw_os_error(hr),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+    #[test]
+    fn trashes_lists_and_restores_through_the_home_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("Trash");
+        let file = dir.path().join("notes 100%.txt");
+        fs::write(&file, b"keep").unwrap();
+
+        let item = xdg::put(&file, &home).unwrap();
+        assert!(!file.exists());
+        assert_eq!(item.id, "notes 100%.txt");
+        let info = fs::read_to_string(home.join("info/notes 100%.txt.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/"));
+        assert!(info.contains("/notes%20100%25.txt\nDeletionDate="));
+
+        // A second file of the same name gets a numbered one
+        fs::write(&file, b"second").unwrap();
+        assert_eq!(xdg::put(&file, &home).unwrap().id, "notes 100%.txt.2");
+
+        let dirs = xdg::trash_dirs(&home);
+        let items: Vec<TrashedItem> = xdg::list(&dirs[..1]);
+        assert_eq!(items.len(), 2);
+        let first = items.iter().find(|i| i.id == "notes 100%.txt").unwrap();
+        assert_eq!(first.original_path, item.original_path);
+        assert!(first.deleted.is_some());
+
+        assert_eq!(restore_trashed(first).unwrap(), item.original_path.unwrap());
+        assert_eq!(fs::read(&file).unwrap(), b"keep");
+        assert!(!home.join("info/notes 100%.txt.trashinfo").exists());
+        // The other one cannot come back over it
+        let second = xdg::list(&dirs[..1]).pop().unwrap();
+        assert!(restore_trashed(&second).is_err());
+        purge_trashed(&second).unwrap();
+        assert!(xdg::list(&dirs[..1]).is_empty());
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+    #[test]
+    fn reads_trash_info_files() {
+        let (path, deleted) = xdg::parse_info(
+            "[Trash Info]\nPath=/home/u/a%20b%C3%A9\nDeletionDate=2024-05-01T10:20:30\n",
+        );
+        assert_eq!(path, Some(PathBuf::from("/home/u/a bé")));
+        assert!(deleted.is_some());
+        assert_eq!(xdg::encode("/tmp/a b%é".as_bytes()), "/tmp/a%20b%25%C3%A9");
+        assert_eq!(xdg::decode("100%"), b"100%");
+        assert_eq!(xdg::parse_info("[Other]\nPath=/x\n"), (None, None));
+    }
+}
@@ -206,6 +206,7 @@ pub enum AstNode<'src> {
     },
     ArrayAssignment {
         name: &'src str,
+        operator: AssignmentOperator,
         elements: Vec<ArrayElement<'src>>,
         is_local: bool,
         is_export: bool,
@@ -309,6 +310,9 @@ pub enum AstNode<'src> {
         left: Box<AstNode<'src>>,
         right: Box<AstNode<'src>>,
     },
+    /// `a &&& b &&& c`: the members run at the same time and the group
+    /// waits for all of them
+    JobGroup(Vec<AstNode<'src>>),
 
     // Argument collections
     ArgumentList(Vec<AstNode<'src>>),
@@ -997,6 +1001,37 @@ impl fmt::Display for AstNode<'_> {
                 }
                 Ok(())
             }
+            AstNode::CommandSubstitution { command, is_legacy } => {
+                if *is_legacy {
+                    write!(f, "`{command}`")
+                } else {
+                    write!(f, "$({command})")
+                }
+            }
+            AstNode::Subshell(body) => write!(f, "({body})"),
+            AstNode::BraceGroup(body) => write!(f, "{{ {body}; }}"),
+            AstNode::Background(job) => write!(f, "{job} &"),
+            AstNode::Sequence { left, right } => write!(f, "{left}; {right}"),
+            AstNode::LogicalAnd { left, right } => write!(f, "{left} && {right}"),
+            AstNode::LogicalOr { left, right } => write!(f, "{left} || {right}"),
+            AstNode::JobGroup(members) => {
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " &&& ")?;
+                    }
+                    write!(f, "{member}")?;
+                }
+                Ok(())
+            }
+            AstNode::Program(statements) => {
+                for (i, statement) in statements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{statement}")?;
+                }
+                Ok(())
+            }
             _ => write!(f, "{self:?}"),
         }
     }
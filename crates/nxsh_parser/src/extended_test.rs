@@ -0,0 +1,320 @@
+//! Parser for the body of the extended test command `[[ ... ]]`.
+//!
+//! Unlike `test`/`[`, the contents of `[[ ]]` are not subject to word
+//! splitting or pathname expansion, `&&`/`||`/`!`/`( )` are operators of the
+//! conditional expression itself, and the right-hand side of `=~` is read as a
+//! raw regular expression (parentheses and `|` included).
+
+use crate::ast::{AstNode, QuoteType, TestOperator, TestUnaryOperator, UnaryOperator};
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Operand word; `quoted` is set when the whole word was quoted
+    Word {
+        text: String,
+        quoted: Option<QuoteType>,
+    },
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn leak(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+/// Split the `[[ ]]` body into tokens, honouring quotes and the raw `=~` operand
+fn tokenize(body: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let after_regex_op =
+            matches!(tokens.last(), Some(Token::Word { text, quoted: None }) if text == "=~");
+
+        if !after_regex_op {
+            if c == '&' && chars.get(i + 1) == Some(&'&') {
+                tokens.push(Token::And);
+                i += 2;
+                continue;
+            }
+            if c == '|' && chars.get(i + 1) == Some(&'|') {
+                tokens.push(Token::Or);
+                i += 2;
+                continue;
+            }
+            if c == '(' {
+                tokens.push(Token::LParen);
+                i += 1;
+                continue;
+            }
+            if c == ')' {
+                tokens.push(Token::RParen);
+                i += 1;
+                continue;
+            }
+            if c == '!'
+                && chars
+                    .get(i + 1)
+                    .is_none_or(|n| n.is_whitespace() || *n == '(')
+            {
+                tokens.push(Token::Not);
+                i += 1;
+                continue;
+            }
+        }
+
+        // Read one word; quoted sections are copied verbatim
+        let mut text = String::new();
+        let mut quote_kinds: Vec<Option<QuoteType>> = Vec::new();
+        let mut depth = 0usize;
+        while i < chars.len() {
+            let ch = chars[i];
+            if ch == '\'' || ch == '"' {
+                let close = chars[i + 1..]
+                    .iter()
+                    .position(|&x| x == ch)
+                    .ok_or_else(|| anyhow!("unterminated quote in [[ ]]"))?;
+                text.extend(&chars[i + 1..i + 1 + close]);
+                quote_kinds.push(Some(if ch == '\'' {
+                    QuoteType::Single
+                } else {
+                    QuoteType::Double
+                }));
+                i += close + 2;
+                continue;
+            }
+            if ch == '\\' && i + 1 < chars.len() {
+                text.push(ch);
+                text.push(chars[i + 1]);
+                quote_kinds.push(None);
+                i += 2;
+                continue;
+            }
+            if after_regex_op {
+                // Regex operands may contain balanced parentheses and `|`
+                if ch == '(' {
+                    depth += 1;
+                } else if ch == ')' {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                } else if ch.is_whitespace() && depth == 0 {
+                    break;
+                }
+            } else if ch.is_whitespace()
+                || ch == '('
+                || ch == ')'
+                || (ch == '&' && chars.get(i + 1) == Some(&'&'))
+                || (ch == '|' && chars.get(i + 1) == Some(&'|'))
+            {
+                break;
+            }
+            text.push(ch);
+            quote_kinds.push(None);
+            i += 1;
+        }
+
+        // A word counts as quoted only when every part of it was quoted
+        let quoted = match quote_kinds.first() {
+            Some(Some(kind)) if quote_kinds.iter().all(|q| q.is_some()) => Some(kind.clone()),
+            _ if quote_kinds.is_empty() => Some(QuoteType::Double),
+            _ => None,
+        };
+        tokens.push(Token::Word { text, quoted });
+    }
+
+    Ok(tokens)
+}
+
+fn unary_operator(op: &str) -> Option<TestUnaryOperator> {
+    Some(match op {
+        "-e" | "-a" => TestUnaryOperator::FileExists,
+        "-f" => TestUnaryOperator::FileRegular,
+        "-d" => TestUnaryOperator::FileDirectory,
+        "-L" | "-h" => TestUnaryOperator::FileSymlink,
+        "-r" => TestUnaryOperator::FileReadable,
+        "-w" => TestUnaryOperator::FileWritable,
+        "-x" => TestUnaryOperator::FileExecutable,
+        "-s" => TestUnaryOperator::FileNonEmpty,
+        "-b" => TestUnaryOperator::FileBlockDevice,
+        "-c" => TestUnaryOperator::FileCharDevice,
+        "-p" => TestUnaryOperator::FileFifo,
+        "-S" => TestUnaryOperator::FileSocket,
+        "-k" => TestUnaryOperator::FileSticky,
+        "-g" => TestUnaryOperator::FileSetgid,
+        "-u" => TestUnaryOperator::FileSetuid,
+        "-O" => TestUnaryOperator::FileOwned,
+        "-G" => TestUnaryOperator::FileGroupOwned,
+        "-N" => TestUnaryOperator::FileModified,
+        "-t" => TestUnaryOperator::FileTty,
+        "-z" => TestUnaryOperator::StringEmpty,
+        "-n" => TestUnaryOperator::StringNonEmpty,
+        "-v" => TestUnaryOperator::VariableSet,
+        _ => return None,
+    })
+}
+
+fn binary_operator(op: &str) -> Option<TestOperator> {
+    Some(match op {
+        "=" | "==" => TestOperator::StringEqual,
+        "!=" => TestOperator::StringNotEqual,
+        "<" => TestOperator::StringLess,
+        ">" => TestOperator::StringGreater,
+        "=~" => TestOperator::StringMatch,
+        "-eq" => TestOperator::NumericEqual,
+        "-ne" => TestOperator::NumericNotEqual,
+        "-lt" => TestOperator::NumericLess,
+        "-le" => TestOperator::NumericLessEqual,
+        "-gt" => TestOperator::NumericGreater,
+        "-ge" => TestOperator::NumericGreaterEqual,
+        "-nt" => TestOperator::FileNewer,
+        "-ot" => TestOperator::FileOlder,
+        "-ef" => TestOperator::FileSame,
+        _ => return None,
+    })
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_word(&self, offset: usize) -> Option<&str> {
+        match self.tokens.get(self.pos + offset) {
+            Some(Token::Word { text, quoted: None }) => Some(text.as_str()),
+            _ => None,
+        }
+    }
+
+    fn operand(&mut self) -> Result<AstNode<'static>> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Word { text, quoted }) => {
+                self.pos += 1;
+                Ok(match quoted {
+                    Some(quote_type) => AstNode::StringLiteral {
+                        value: leak(&text),
+                        quote_type,
+                    },
+                    None => AstNode::Word(leak(&text)),
+                })
+            }
+            other => Err(anyhow!("[[ ]]: expected operand, found {other:?}")),
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<AstNode<'static>> {
+        let mut left = self.and_expr()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.and_expr()?;
+            left = AstNode::LogicalOr {
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<AstNode<'static>> {
+        let mut left = self.not_expr()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.not_expr()?;
+            left = AstNode::LogicalAnd {
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<AstNode<'static>> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            let operand = self.not_expr()?;
+            return Ok(AstNode::UnaryExpression {
+                operator: UnaryOperator::LogicalNot,
+                operand: Box::new(operand),
+            });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<AstNode<'static>> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let inner = self.or_expr()?;
+            if self.peek() != Some(&Token::RParen) {
+                return Err(anyhow!("[[ ]]: expected `)`"));
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+
+        // Binary form takes precedence: `-n == -n` compares two strings
+        if let Some(op) = self.peek_word(1).and_then(binary_operator) {
+            if matches!(self.tokens.get(self.pos + 2), Some(Token::Word { .. })) {
+                let left = self.operand()?;
+                self.pos += 1;
+                let right = self.operand()?;
+                return Ok(AstNode::TestBinary {
+                    left: Box::new(left),
+                    operator: op,
+                    right: Box::new(right),
+                });
+            }
+        }
+
+        if let Some(op) = self.peek_word(0).and_then(unary_operator) {
+            if matches!(self.tokens.get(self.pos + 1), Some(Token::Word { .. })) {
+                self.pos += 1;
+                let operand = self.operand()?;
+                return Ok(AstNode::TestUnary {
+                    operator: op,
+                    operand: Box::new(operand),
+                });
+            }
+        }
+
+        // A lone word is true when non-empty
+        let operand = self.operand()?;
+        Ok(AstNode::TestUnary {
+            operator: TestUnaryOperator::StringNonEmpty,
+            operand: Box::new(operand),
+        })
+    }
+}
+
+/// Parse the text between `[[` and `]]` into a `TestExpression` node
+pub(crate) fn parse_extended_test(body: &str) -> Result<AstNode<'static>> {
+    let tokens = tokenize(body)?;
+    if tokens.is_empty() {
+        return Err(anyhow!("[[ ]]: empty conditional expression"));
+    }
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let condition = parser.or_expr()?;
+    if let Some(tok) = parser.peek() {
+        return Err(anyhow!("[[ ]]: unexpected token {tok:?}"));
+    }
+    Ok(AstNode::TestExpression {
+        condition: Box::new(condition),
+        is_extended: true,
+    })
+}
@@ -1,6 +1,7 @@
 #![doc = "Command-line parser turning raw input into an AST."]
 
 pub mod ast;
+mod extended_test;
 pub mod lexer;
 
 #[cfg(test)]
@@ -206,6 +207,20 @@ impl ShellCommandParser {
                         return Err(anyhow::anyhow!("Unexpected statement sequence"));
                     }
                 }
+                Rule::group_op => {
+                    // Handle &&& operator: run the next command alongside the current ones
+                    if let Some(left) = current_node.take() {
+                        i += 1; // Move to next statement
+                        if i < inner_pairs.len() && inner_pairs[i].as_rule() == Rule::statement {
+                            let right = self.parse_statement(inner_pairs[i].clone(), input)?;
+                            current_node = Some(self.join_group(left, right));
+                        } else {
+                            return Err(anyhow::anyhow!("Expected statement after &&& operator"));
+                        }
+                    } else {
+                        return Err(anyhow::anyhow!("No left operand for &&& operator"));
+                    }
+                }
                 Rule::and_op => {
                     #[cfg(debug_assertions)]
                     #[cfg(feature = "debug_parse")]
@@ -298,6 +313,17 @@ impl ShellCommandParser {
                 Rule::command => {
                     return self.parse_command(inner_pair, input);
                 }
+                Rule::assignment_statement => {
+                    let mut assignments = inner_pair
+                        .into_inner()
+                        .map(|a| self.parse_assignment(a))
+                        .collect::<Result<Vec<_>>>()?;
+                    return Ok(if assignments.len() == 1 {
+                        assignments.remove(0)
+                    } else {
+                        ast::AstNode::StatementList(assignments)
+                    });
+                }
                 Rule::if_statement => {
                     return self.parse_if_statement(inner_pair, input);
                 }
@@ -307,12 +333,24 @@ impl ShellCommandParser {
                 Rule::while_statement => {
                     return self.parse_while_statement(inner_pair, input);
                 }
+                Rule::until_statement => {
+                    return self.parse_until_statement(inner_pair, input);
+                }
+                Rule::for_arith_statement => {
+                    return self.parse_for_arith_statement(inner_pair, input);
+                }
                 Rule::case_statement => {
                     return self.parse_case_statement(inner_pair, input);
                 }
                 Rule::select_statement => {
                     return self.parse_select_statement(inner_pair, input);
                 }
+                Rule::try_statement => {
+                    return self.parse_try_statement(inner_pair, input);
+                }
+                Rule::throw_statement => {
+                    return self.parse_throw_statement(inner_pair, input);
+                }
                 Rule::function_def => {
                     return self.parse_function_def(inner_pair, input);
                 }
@@ -328,6 +366,9 @@ impl ShellCommandParser {
                 Rule::closure_expr => {
                     return self.parse_closure_expr(inner_pair, input);
                 }
+                Rule::extended_test => {
+                    return self.parse_extended_test(inner_pair);
+                }
                 _ => {}
             }
         }
@@ -418,6 +459,96 @@ impl ShellCommandParser {
         })
     }
 
+    /// Parse `try { ... } catch err { ... } finally { ... }`
+    fn parse_try_statement(&self, pair: Pair<Rule>, input: &str) -> Result<ast::AstNode<'static>> {
+        let mut body = None;
+        let mut catch_clauses = Vec::new();
+        let mut finally_clause = None;
+
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::try_block => body = Some(self.parse_try_block(inner, input)?),
+                Rule::catch_clause => {
+                    let mut variable = None;
+                    let mut catch_body = None;
+                    for part in inner.into_inner() {
+                        match part.as_rule() {
+                            Rule::identifier => variable = Some(self.leak_string(part.as_str())),
+                            Rule::try_block => {
+                                catch_body = Some(self.parse_try_block(part, input)?)
+                            }
+                            _ => {}
+                        }
+                    }
+                    let catch_body =
+                        catch_body.ok_or_else(|| anyhow::anyhow!("Catch clause missing body"))?;
+                    catch_clauses.push(ast::CatchClause {
+                        variable,
+                        body: Box::new(catch_body),
+                    });
+                }
+                Rule::finally_clause => {
+                    let block = inner.into_inner().find(|p| p.as_rule() == Rule::try_block);
+                    if let Some(block) = block {
+                        finally_clause = Some(Box::new(self.parse_try_block(block, input)?));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let body = body.ok_or_else(|| anyhow::anyhow!("Try statement missing body"))?;
+        Ok(ast::AstNode::Try {
+            body: Box::new(body),
+            catch_clauses,
+            finally_clause,
+        })
+    }
+
+    /// Statements between the braces of a try, catch or finally block
+    fn parse_try_block(&self, pair: Pair<Rule>, input: &str) -> Result<ast::AstNode<'static>> {
+        let body = pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::inner_program)
+            .map(|p| self.build_ast_from_pairs(p.into_inner(), input))
+            .transpose()?
+            .unwrap_or_else(|| ast::AstNode::Program(Vec::new()));
+        Ok(self.normalize_block(body))
+    }
+
+    /// Parse `throw message [code]`; a quoted message loses its quotes, a
+    /// bare `throw` raises an empty message and a code makes the value an
+    /// argument list of both
+    fn parse_throw_statement(
+        &self,
+        pair: Pair<Rule>,
+        input: &str,
+    ) -> Result<ast::AstNode<'static>> {
+        let mut parts = Vec::new();
+        for arg in pair.into_inner().filter(|p| p.as_rule() == Rule::argument) {
+            let text = arg.as_str();
+            parts.push(match text.chars().next() {
+                Some(quote @ ('"' | '\'')) if text.len() >= 2 && text.ends_with(quote) => {
+                    ast::AstNode::StringLiteral {
+                        value: self.leak_string(&text[1..text.len() - 1]),
+                        quote_type: if quote == '"' {
+                            ast::QuoteType::Double
+                        } else {
+                            ast::QuoteType::Single
+                        },
+                    }
+                }
+                _ => self.parse_argument(arg, input)?,
+            });
+        }
+        let value = match parts.len() {
+            0 => ast::AstNode::Word(""),
+            1 => parts.remove(0),
+            _ => ast::AstNode::ArgumentList(parts),
+        };
+        Ok(ast::AstNode::ThrowStatement(Box::new(value)))
+    }
+
     /// Parse a command (simple command or pipeline)
     fn parse_command(&self, pair: Pair<Rule>, input: &str) -> Result<ast::AstNode<'static>> {
         for inner_pair in pair.into_inner() {
@@ -459,12 +590,13 @@ impl ShellCommandParser {
                                 found = true;
                             }
                             Rule::subshell => {
-                                // For now treat subshell as its own node
-                                let text = ce_inner.as_str();
-                                let node = ast::AstNode::Subshell(Box::new(ast::AstNode::Word(
-                                    self.leak_string(text),
-                                )));
-                                commands.push(node);
+                                let body = ce_inner
+                                    .into_inner()
+                                    .find(|p| p.as_rule() == Rule::inner_program)
+                                    .map(|p| self.build_ast_from_pairs(p.into_inner(), input))
+                                    .transpose()?
+                                    .unwrap_or_else(|| ast::AstNode::Program(Vec::new()));
+                                commands.push(ast::AstNode::Subshell(Box::new(body)));
                                 found = true;
                             }
                             _ => {}
@@ -566,37 +698,7 @@ impl ShellCommandParser {
         for inner_pair in pair.into_inner() {
             match inner_pair.as_rule() {
                 Rule::assignment => {
-                    // identifier '=' assignment_value
-                    let mut name: Option<&str> = None;
-                    let mut value: Option<&str> = None;
-                    for a in inner_pair.clone().into_inner() {
-                        if a.as_rule() == Rule::identifier && name.is_none() {
-                            name = Some(self.leak_string(a.as_str()));
-                        }
-                    }
-                    // Fallback: raw text split
-                    if name.is_none() {
-                        let text = inner_pair.as_str();
-                        if let Some(pos) = text.find('=') {
-                            name = Some(self.leak_string(&text[..pos]));
-                            value = Some(self.leak_string(&text[pos + 1..]));
-                        }
-                    } else {
-                        let text = inner_pair.as_str();
-                        if let Some(pos) = text.find('=') {
-                            value = Some(self.leak_string(&text[pos + 1..]));
-                        }
-                    }
-                    let name = name.ok_or_else(|| anyhow::anyhow!("Invalid assignment"))?;
-                    let val_node = ast::AstNode::Word(value.unwrap_or(""));
-                    return Ok(ast::AstNode::VariableAssignment {
-                        name,
-                        operator: ast::AssignmentOperator::Assign,
-                        value: Box::new(val_node),
-                        is_local: false,
-                        is_export: false,
-                        is_readonly: false,
-                    });
+                    return self.parse_assignment(inner_pair);
                 }
                 Rule::closure_expr => {
                     return self.parse_closure_expr(inner_pair, _input);
@@ -605,19 +707,7 @@ impl ShellCommandParser {
                     return Ok(ast::AstNode::Word(self.leak_string(inner_pair.as_str())));
                 }
                 Rule::variable => {
-                    let var_text = inner_pair.as_str();
-                    // Remove $ prefix
-                    let var_name = if var_text.starts_with("${") && var_text.ends_with("}") {
-                        &var_text[2..var_text.len() - 1]
-                    } else if let Some(rest) = var_text.strip_prefix("$") {
-                        rest
-                    } else {
-                        var_text
-                    };
-                    return Ok(ast::AstNode::VariableExpansion {
-                        name: self.leak_string(var_name),
-                        modifier: None,
-                    });
+                    return Ok(self.parse_variable(inner_pair));
                 }
                 Rule::command_substitution => {
                     let sub_text = inner_pair.as_str();
@@ -625,22 +715,22 @@ impl ShellCommandParser {
 
                     // Extract the command part
                     let command_str = if is_legacy {
-                        // Legacy backtick syntax: `command`
-                        &sub_text[1..sub_text.len() - 1]
+                        // Legacy backtick syntax: `command`, nested as `outer \`inner\``
+                        unescape_backquoted(&sub_text[1..sub_text.len() - 1])
                     } else {
-                        // Modern syntax: $(command)
-                        &sub_text[2..sub_text.len() - 1]
+                        // Modern syntax: $(command), which nests without escaping
+                        sub_text[2..sub_text.len() - 1].to_string()
                     };
 
                     // Parse the inner command (recursively parse for proper semantics)
                     let inner_command = if command_str.trim().is_empty() {
                         ast::AstNode::Word(self.leak_string(""))
                     } else {
-                        match self.parse(command_str) {
+                        match self.parse(&command_str) {
                             Ok(node) => node,
                             Err(_) => {
                                 // Fallback to raw word if nested parse fails
-                                ast::AstNode::Word(self.leak_string(command_str))
+                                ast::AstNode::Word(self.leak_string(&command_str))
                             }
                         }
                     };
@@ -657,18 +747,119 @@ impl ShellCommandParser {
         Err(anyhow::anyhow!("Unable to parse argument"))
     }
 
+    /// Parse `NAME=value`, `NAME+=value`, `NAME[sub]=value` or `NAME=(elem ...)`.
+    /// Element assignments keep the subscript in the name (`a[1]`).
+    fn parse_assignment(&self, pair: Pair<Rule>) -> Result<ast::AstNode<'static>> {
+        let mut name = String::new();
+        let mut operator = ast::AssignmentOperator::Assign;
+        let mut value = "";
+        let mut elements = None;
+        for part in pair.into_inner() {
+            match part.as_rule() {
+                Rule::assignment_name | Rule::array_subscript => name.push_str(part.as_str()),
+                Rule::assign_op if part.as_str() == "+=" => {
+                    operator = ast::AssignmentOperator::AddAssign;
+                }
+                Rule::assignment_value => value = self.leak_string(part.as_str()),
+                Rule::array_literal => elements = Some(self.parse_array_literal(part)),
+                _ => {}
+            }
+        }
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Invalid assignment"));
+        }
+        let name = self.leak_string(&name);
+        Ok(match elements {
+            Some(elements) => ast::AstNode::ArrayAssignment {
+                name,
+                operator,
+                elements,
+                is_local: false,
+                is_export: false,
+            },
+            None => ast::AstNode::VariableAssignment {
+                name,
+                operator,
+                value: Box::new(ast::AstNode::Word(value)),
+                is_local: false,
+                is_export: false,
+                is_readonly: false,
+            },
+        })
+    }
+
+    /// Parse the elements of `( ... )`; `[sub]=value` sets an explicit index or key
+    fn parse_array_literal(&self, pair: Pair<Rule>) -> Vec<ast::ArrayElement<'static>> {
+        let mut elements = Vec::new();
+        for element in pair.into_inner() {
+            let mut index = None;
+            let mut value = "";
+            for part in element.into_inner() {
+                match part.as_rule() {
+                    Rule::array_subscript => {
+                        let sub = part.as_str();
+                        index = Some(ast::AstNode::Word(self.leak_string(&sub[1..sub.len() - 1])));
+                    }
+                    Rule::array_value => value = self.leak_string(part.as_str()),
+                    _ => {}
+                }
+            }
+            elements.push(ast::ArrayElement {
+                index,
+                value: ast::AstNode::Word(value),
+            });
+        }
+        elements
+    }
+
+    /// Parse `$name`, `${name}`, `${name[sub]}`, `${#name}` (length) and
+    /// `${!name}` / `${!name[@]}` (indirection, array keys)
+    fn parse_variable(&self, pair: Pair<Rule>) -> ast::AstNode<'static> {
+        let mut name = String::new();
+        let mut modifier = None;
+        for part in pair.clone().into_inner() {
+            match part.as_rule() {
+                Rule::param_prefix if part.as_str() == "#" => {
+                    modifier = Some(ast::ParameterModifier::Length);
+                }
+                Rule::param_prefix => name.push('!'),
+                _ => name.push_str(part.as_str()),
+            }
+        }
+        if name.is_empty() {
+            // Digits in `${10}` are not a separate pair
+            let text = pair.as_str();
+            name = text
+                .strip_prefix("${")
+                .and_then(|t| t.strip_suffix('}'))
+                .or_else(|| text.strip_prefix('$'))
+                .unwrap_or(text)
+                .to_string();
+        }
+        ast::AstNode::VariableExpansion {
+            name: self.leak_string(&name),
+            modifier,
+        }
+    }
+
     /// Parse a redirection
     fn parse_redirection(
         &self,
         pair: Pair<Rule>,
         _input: &str,
     ) -> Result<ast::Redirection<'static>> {
+        let mut fd = None;
         let mut operator = None;
         let mut redir_type = None;
         let mut target = None;
 
         for inner_pair in pair.into_inner() {
             match inner_pair.as_rule() {
+                Rule::io_number => {
+                    fd = Some(inner_pair.as_str().parse::<u32>().map_err(|_| {
+                        anyhow::anyhow!("Invalid file descriptor '{}'", inner_pair.as_str())
+                    })?);
+                }
                 Rule::redirect_in => {
                     operator = Some(ast::RedirectionOperator::Input);
                     redir_type = Some(ast::RedirectionType::Input);
@@ -689,6 +880,20 @@ impl ShellCommandParser {
                     operator = Some(ast::RedirectionOperator::OutputBoth);
                     redir_type = Some(ast::RedirectionType::Both);
                 }
+                Rule::redirect_dup_in => {
+                    operator = Some(ast::RedirectionOperator::DuplicateInput);
+                    redir_type = Some(ast::RedirectionType::Input);
+                }
+                Rule::redirect_dup_out => {
+                    operator = Some(ast::RedirectionOperator::DuplicateOutput);
+                    redir_type = Some(ast::RedirectionType::Output);
+                }
+                Rule::fd_target => {
+                    target = Some(match inner_pair.as_str().parse::<u32>() {
+                        Ok(n) => ast::RedirectionTarget::FileDescriptor(n),
+                        Err(_) => ast::RedirectionTarget::Close,
+                    });
+                }
                 Rule::word => {
                     let word_node = ast::AstNode::Word(self.leak_string(inner_pair.as_str()));
                     target = Some(ast::RedirectionTarget::File(Box::new(word_node)));
@@ -704,7 +909,7 @@ impl ShellCommandParser {
         let target = target.ok_or_else(|| anyhow::anyhow!("Redirection must have a target"))?;
 
         Ok(ast::Redirection {
-            fd: None,
+            fd,
             operator,
             target,
             redir_type,
@@ -960,13 +1165,40 @@ impl ShellCommandParser {
         pair: Pair<Rule>,
         input: &str,
     ) -> Result<ast::AstNode<'static>> {
+        let (condition, body) = self.parse_loop_condition_and_body(pair, input, "While")?;
+        Ok(ast::AstNode::While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        })
+    }
+
+    /// Parse until statement (loops while the condition fails)
+    fn parse_until_statement(
+        &self,
+        pair: Pair<Rule>,
+        input: &str,
+    ) -> Result<ast::AstNode<'static>> {
+        let (condition, body) = self.parse_loop_condition_and_body(pair, input, "Until")?;
+        Ok(ast::AstNode::Until {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        })
+    }
+
+    /// Shared condition/body extraction for `while` and `until` loops
+    fn parse_loop_condition_and_body(
+        &self,
+        pair: Pair<Rule>,
+        input: &str,
+        kind: &str,
+    ) -> Result<(ast::AstNode<'static>, ast::AstNode<'static>)> {
         let mut condition: Option<ast::AstNode<'static>> = None;
         let mut body: Option<ast::AstNode<'static>> = None;
         let mut current_state = WhileParseState::Condition;
 
         for inner_pair in pair.into_inner() {
             match inner_pair.as_rule() {
-                Rule::while_kw => {
+                Rule::while_kw | Rule::until_kw => {
                     current_state = WhileParseState::Condition;
                 }
                 Rule::test_command => {
@@ -974,7 +1206,8 @@ impl ShellCommandParser {
                         condition = Some(self.parse_test_command(inner_pair, input)?);
                     } else {
                         return Err(anyhow::anyhow!(
-                            "Unexpected test_command in while statement"
+                            "Unexpected test_command in {} statement",
+                            kind.to_lowercase()
                         ));
                     }
                 }
@@ -982,7 +1215,10 @@ impl ShellCommandParser {
                     if current_state == WhileParseState::Condition {
                         condition = Some(self.parse_command(inner_pair, input)?);
                     } else {
-                        return Err(anyhow::anyhow!("Unexpected command in while statement"));
+                        return Err(anyhow::anyhow!(
+                            "Unexpected command in {} statement",
+                            kind.to_lowercase()
+                        ));
                     }
                 }
                 Rule::do_kw => {
@@ -1002,7 +1238,7 @@ impl ShellCommandParser {
                     }
                 }
                 Rule::done_kw => {
-                    // End of while statement
+                    // End of loop statement
                     break;
                 }
                 _ => {
@@ -1013,11 +1249,56 @@ impl ShellCommandParser {
 
         // Validate required components
         let condition =
-            condition.ok_or_else(|| anyhow::anyhow!("While statement missing condition"))?;
-        let body = body.ok_or_else(|| anyhow::anyhow!("While statement missing body"))?;
+            condition.ok_or_else(|| anyhow::anyhow!("{kind} statement missing condition"))?;
+        let body = body.ok_or_else(|| anyhow::anyhow!("{kind} statement missing body"))?;
 
-        Ok(ast::AstNode::While {
-            condition: Box::new(condition),
+        Ok((condition, body))
+    }
+
+    /// Parse C-style arithmetic for statement: `for ((init; cond; update)); do ...; done`
+    ///
+    /// Each clause is kept as raw arithmetic text and evaluated by the executor.
+    fn parse_for_arith_statement(
+        &self,
+        pair: Pair<Rule>,
+        input: &str,
+    ) -> Result<ast::AstNode<'static>> {
+        let mut init: Option<Box<ast::AstNode<'static>>> = None;
+        let mut condition: Option<Box<ast::AstNode<'static>>> = None;
+        let mut update: Option<Box<ast::AstNode<'static>>> = None;
+        let mut body: Option<ast::AstNode<'static>> = None;
+
+        let clause = |p: Pair<Rule>| -> Option<Box<ast::AstNode<'static>>> {
+            let text = p.as_str().trim();
+            if text.is_empty() {
+                None
+            } else {
+                Some(Box::new(ast::AstNode::ArithmeticExpansion {
+                    expr: Box::new(ast::AstNode::Word(self.leak_string(text))),
+                    is_legacy: false,
+                }))
+            }
+        };
+
+        for inner_pair in pair.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::arith_init => init = clause(inner_pair),
+                Rule::arith_cond => condition = clause(inner_pair),
+                Rule::arith_update => update = clause(inner_pair),
+                Rule::command_list => {
+                    body = Some(self.normalize_block(self.parse_command_list(inner_pair, input)?));
+                }
+                Rule::done_kw => break,
+                _ => {}
+            }
+        }
+
+        let body = body.ok_or_else(|| anyhow::anyhow!("For statement missing body"))?;
+
+        Ok(ast::AstNode::ForC {
+            init,
+            condition,
+            update,
             body: Box::new(body),
         })
     }
@@ -1140,7 +1421,8 @@ impl ShellCommandParser {
         }
     }
 
-    /// Parse function definition with name, parameters, and body
+    /// Parse function definition with name, parameters, and body, written
+    /// `function name(params) { ... }` or the POSIX `name() { ... }`
     fn parse_function_def(&self, pair: Pair<Rule>, input: &str) -> Result<ast::AstNode<'static>> {
         let mut name: Option<&str> = None;
         let mut params = Vec::new();
@@ -1196,6 +1478,29 @@ impl ShellCommandParser {
                         body = Some(ast::AstNode::Program(statements));
                     }
                 }
+                Rule::parameter_list => {
+                    for param in inner_pair.into_inner() {
+                        if param.as_rule() == Rule::identifier {
+                            params.push(ast::Parameter {
+                                name: self.leak_string(param.as_str()),
+                                default: None,
+                                is_variadic: false,
+                            });
+                        }
+                    }
+                }
+                Rule::brace_group => {
+                    // { statement_list } の中身を本体とする
+                    let mut statements = Vec::new();
+                    for list in inner_pair.into_inner() {
+                        for stmt in list.into_inner() {
+                            if stmt.as_rule() == Rule::statement {
+                                statements.push(self.parse_statement(stmt, input)?);
+                            }
+                        }
+                    }
+                    body = Some(ast::AstNode::Program(statements));
+                }
                 _ => {
                     // Handle transitions based on literal characters
                     match inner_pair.as_str() {
@@ -1469,30 +1774,51 @@ impl ShellCommandParser {
         })
     }
 
-    /// Mark an AST node as background
-    fn mark_background(&self, mut node: ast::AstNode<'static>) -> ast::AstNode<'static> {
-        match &mut node {
-            ast::AstNode::Command { background, .. } => {
-                *background = true;
-            }
-            ast::AstNode::Pipeline { elements, .. } => {
-                if elements.len() == 1 {
-                    // Unwrap single-element pipeline into a command with background
-                    let mut only = elements.remove(0);
-                    if let ast::AstNode::Command { background, .. } = &mut only {
-                        *background = true;
-                    }
-                    return only;
-                } else if let Some(ast::AstNode::Command { background, .. }) = elements.last_mut() {
-                    *background = true;
-                }
+    /// Mark an AST node as background. A simple command carries the flag
+    /// itself; in `a; b &` only `b` is put in the background, and any other
+    /// node (pipelines, `&&`/`||` lists, subshells) becomes one `Background` job.
+    fn mark_background(&self, node: ast::AstNode<'static>) -> ast::AstNode<'static> {
+        match node {
+            ast::AstNode::Command {
+                name,
+                args,
+                redirections,
+                ..
+            } => ast::AstNode::Command {
+                name,
+                args,
+                redirections,
+                background: true,
+            },
+            ast::AstNode::Pipeline { mut elements, .. } if elements.len() == 1 => {
+                self.mark_background(elements.remove(0))
             }
-            _ => {
-                // For other types, wrap in a command-like structure
-                // This is a simplification - real implementation would be more sophisticated
+            ast::AstNode::Sequence { left, right } => ast::AstNode::Sequence {
+                left,
+                right: Box::new(self.mark_background(*right)),
+            },
+            other => ast::AstNode::Background(Box::new(other)),
+        }
+    }
+
+    /// Add `right` to the `&&&` group that `left` ends with. `;` binds more
+    /// loosely, so only the last command of a sequence joins the group.
+    fn join_group(
+        &self,
+        left: ast::AstNode<'static>,
+        right: ast::AstNode<'static>,
+    ) -> ast::AstNode<'static> {
+        match left {
+            ast::AstNode::Sequence { left, right: last } => ast::AstNode::Sequence {
+                left,
+                right: Box::new(self.join_group(*last, right)),
+            },
+            ast::AstNode::JobGroup(mut members) => {
+                members.push(right);
+                ast::AstNode::JobGroup(members)
             }
+            other => ast::AstNode::JobGroup(vec![other, right]),
         }
-        node
     }
 
     /// Parse a test command (command with optional semicolon)
@@ -1502,6 +1828,9 @@ impl ShellCommandParser {
                 Rule::command => {
                     return self.parse_command(inner_pair, input);
                 }
+                Rule::extended_test => {
+                    return self.parse_extended_test(inner_pair);
+                }
                 Rule::semicolon => {
                     // Ignore semicolon
                 }
@@ -1511,6 +1840,16 @@ impl ShellCommandParser {
         Err(anyhow::anyhow!("Unable to parse test command"))
     }
 
+    /// Parse an extended test command `[[ ... ]]`
+    fn parse_extended_test(&self, pair: Pair<Rule>) -> Result<ast::AstNode<'static>> {
+        let body = pair
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::extended_test_body)
+            .map(|p| p.as_str())
+            .unwrap_or("");
+        extended_test::parse_extended_test(body)
+    }
+
     /// Parse a command list
     fn parse_command_list(&self, pair: Pair<Rule>, input: &str) -> Result<ast::AstNode<'static>> {
         let mut statements = Vec::new();
@@ -1569,4 +1908,23 @@ pub fn parse(input: &str) -> Result<ast::AstNode> {
     }
 }
 
+/// Body of a `` `cmd` `` substitution with its backslash escapes removed.
+/// Inside backquotes a backslash only escapes `` ` ``, `$` and `\\`; escaped
+/// backquotes are how one backquoted substitution nests inside another.
+pub fn unescape_backquoted(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next @ ('`' | '$' | '\\')) = chars.peek() {
+                out.push(next);
+                chars.next();
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
 pub use lexer::TokenKind;
@@ -439,3 +439,36 @@ fn test_mixed_content() {
         }
     }
 }
+
+/// Test descriptor numbers and duplication in redirections
+#[test]
+fn test_redirection_descriptors() {
+    use crate::ast::{RedirectionOperator, RedirectionTarget};
+
+    let parser = ShellCommandParser::new();
+    let result = parser.parse("exec 3<input 2>&1 4>&- 5 >out").unwrap();
+
+    match result {
+        AstNode::Command {
+            args, redirections, ..
+        } => {
+            // A number separated from the operator stays an argument
+            assert!(matches!(args.as_slice(), [AstNode::Word("5")]), "{args:?}");
+            assert_eq!(redirections.len(), 4);
+            assert_eq!(redirections[0].fd, Some(3));
+            assert_eq!(redirections[0].operator, RedirectionOperator::Input);
+            assert_eq!(redirections[1].fd, Some(2));
+            assert_eq!(
+                redirections[1].operator,
+                RedirectionOperator::DuplicateOutput
+            );
+            assert_eq!(redirections[1].target, RedirectionTarget::FileDescriptor(1));
+            assert_eq!(redirections[2].target, RedirectionTarget::Close);
+            assert_eq!(redirections[3].fd, None);
+        }
+        _ => {
+            eprintln!("Expected Command node, got {result:?}");
+            panic!("Expected Command node");
+        }
+    }
+}
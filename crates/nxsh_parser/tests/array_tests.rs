@@ -0,0 +1,71 @@
+use nxsh_parser::ast::{AssignmentOperator, AstNode, ParameterModifier};
+use nxsh_parser::ShellCommandParser;
+
+fn parse(src: &str) -> AstNode<'static> {
+    ShellCommandParser::new().parse(src).unwrap()
+}
+
+#[test]
+fn parse_array_literal_assignment() {
+    match parse("a=(one 'two words' [5]=six)") {
+        AstNode::ArrayAssignment {
+            name,
+            operator,
+            elements,
+            ..
+        } => {
+            assert_eq!(name, "a");
+            assert_eq!(operator, AssignmentOperator::Assign);
+            assert_eq!(elements.len(), 3);
+            assert_eq!(elements[1].value, AstNode::Word("'two words'"));
+            assert_eq!(elements[2].index, Some(AstNode::Word("5")));
+            assert_eq!(elements[2].value, AstNode::Word("six"));
+        }
+        other => panic!("expected array assignment, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_element_and_append_assignments() {
+    match parse("a[i+1]=x") {
+        AstNode::VariableAssignment { name, value, .. } => {
+            assert_eq!(name, "a[i+1]");
+            assert_eq!(*value, AstNode::Word("x"));
+        }
+        other => panic!("expected assignment, got {other:?}"),
+    }
+    assert!(matches!(
+        parse("a+=(y)"),
+        AstNode::ArrayAssignment {
+            operator: AssignmentOperator::AddAssign,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn parse_array_expansions() {
+    let args = match parse("echo ${a[1]} ${#a[@]} ${!m[@]} ${#s}") {
+        AstNode::Command { args, .. } => args,
+        other => panic!("expected command, got {other:?}"),
+    };
+    let expansions: Vec<_> = args
+        .iter()
+        .map(|arg| match arg {
+            AstNode::VariableExpansion { name, modifier } => (
+                name.to_string(),
+                matches!(modifier, Some(ParameterModifier::Length)),
+            ),
+            other => panic!("expected expansion, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(
+        expansions,
+        vec![
+            ("a[1]".to_string(), false),
+            ("a[@]".to_string(), true),
+            ("!m[@]".to_string(), false),
+            ("s".to_string(), true),
+        ]
+    );
+}
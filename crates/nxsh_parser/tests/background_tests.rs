@@ -0,0 +1,55 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+fn parse(src: &str) -> AstNode<'static> {
+    ShellCommandParser::new().parse(src).unwrap()
+}
+
+#[test]
+fn simple_command_keeps_background_flag() {
+    assert!(matches!(
+        parse("sleep 1 &"),
+        AstNode::Command {
+            background: true,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn pipelines_and_subshells_become_one_job() {
+    match parse("sleep 1 | cat &") {
+        AstNode::Background(job) => {
+            assert!(matches!(*job, AstNode::Pipeline { .. }));
+            assert_eq!(job.to_string(), "sleep 1 | cat");
+        }
+        other => panic!("expected background job, got {other:?}"),
+    }
+    match parse("(sleep 1; echo done) &") {
+        AstNode::Background(job) => assert!(matches!(*job, AstNode::Subshell(_))),
+        other => panic!("expected background job, got {other:?}"),
+    }
+}
+
+#[test]
+fn only_last_list_element_goes_to_background() {
+    match parse("echo a; sleep 1 &") {
+        AstNode::Sequence { left, right } => {
+            assert!(matches!(
+                *left,
+                AstNode::Command {
+                    background: false,
+                    ..
+                }
+            ));
+            assert!(matches!(
+                *right,
+                AstNode::Command {
+                    background: true,
+                    ..
+                }
+            ));
+        }
+        other => panic!("expected sequence, got {other:?}"),
+    }
+}
@@ -0,0 +1,58 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::{unescape_backquoted, ShellCommandParser};
+
+fn parse(src: &str) -> AstNode<'static> {
+    ShellCommandParser::new().parse(src).unwrap()
+}
+
+fn args(node: &AstNode<'static>) -> Vec<AstNode<'static>> {
+    match node {
+        AstNode::Command { args, .. } => args.clone(),
+        other => panic!("expected command, got {other:?}"),
+    }
+}
+
+fn substituted(node: &AstNode<'static>) -> (AstNode<'static>, bool) {
+    match node {
+        AstNode::CommandSubstitution { command, is_legacy } => ((**command).clone(), *is_legacy),
+        other => panic!("expected command substitution, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_nested_substitution_with_quotes() {
+    let outer = args(&parse("echo $(echo $(date) \"x)\")"));
+    assert_eq!(outer.len(), 1);
+    let (inner, legacy) = substituted(&outer[0]);
+    assert!(!legacy);
+    let inner_args = args(&inner);
+    assert_eq!(inner_args.len(), 2);
+    let (date, _) = substituted(&inner_args[0]);
+    assert!(matches!(date, AstNode::Command { .. }));
+    assert_eq!(inner_args[1], AstNode::Word("\"x)\""));
+}
+
+#[test]
+fn parse_nested_backquotes() {
+    let outer = args(&parse("echo `echo \\`date\\``"));
+    let (inner, legacy) = substituted(&outer[0]);
+    assert!(legacy);
+    let (_, nested_legacy) = substituted(&args(&inner)[0]);
+    assert!(nested_legacy);
+    assert_eq!(unescape_backquoted("a \\`b\\` \\$c \\d"), "a `b` $c \\d");
+}
+
+#[test]
+fn substitution_inside_words_and_assignments() {
+    assert_eq!(
+        args(&parse("echo $(date)x \"pre $(date)\"")),
+        vec![AstNode::Word("$(date)x"), AstNode::Word("\"pre $(date)\"")]
+    );
+    match parse("x=$(echo a b)") {
+        AstNode::VariableAssignment { name, value, .. } => {
+            assert_eq!(name, "x");
+            assert_eq!(*value, AstNode::Word("$(echo a b)"));
+        }
+        other => panic!("expected assignment, got {other:?}"),
+    }
+}
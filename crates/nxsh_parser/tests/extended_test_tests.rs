@@ -0,0 +1,44 @@
+use nxsh_parser::ast::{AstNode, TestOperator};
+use nxsh_parser::ShellCommandParser;
+
+#[test]
+fn parse_extended_test_with_glob_and_logic() {
+    let p = ShellCommandParser::new();
+    let ast = p.parse("[[ -n $name && $name == foo* ]]").unwrap();
+    match ast {
+        AstNode::TestExpression {
+            condition,
+            is_extended,
+        } => {
+            assert!(is_extended);
+            assert!(matches!(*condition, AstNode::LogicalAnd { .. }));
+        }
+        other => panic!("expected TestExpression, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_extended_test_regex_operand_is_raw() {
+    let p = ShellCommandParser::new();
+    let ast = p.parse("[[ $v =~ ^(a|b)+$ ]]").unwrap();
+    let AstNode::TestExpression { condition, .. } = ast else {
+        panic!("expected TestExpression");
+    };
+    match *condition {
+        AstNode::TestBinary {
+            operator, right, ..
+        } => {
+            assert_eq!(operator, TestOperator::StringMatch);
+            assert_eq!(*right, AstNode::Word("^(a|b)+$"));
+        }
+        other => panic!("expected TestBinary, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_extended_test_as_if_condition() {
+    let p = ShellCommandParser::new();
+    assert!(p
+        .parse("if [[ \"a b\" != 'c' ]]; then echo yes; fi")
+        .is_ok());
+}
@@ -0,0 +1,29 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+fn function(src: &str) -> (String, usize) {
+    match ShellCommandParser::new().parse(src).unwrap() {
+        AstNode::Function { name, body, .. } => match *body {
+            AstNode::Program(statements) => (name.to_string(), statements.len()),
+            other => panic!("unexpected body {other:?}"),
+        },
+        other => panic!("expected a function, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_posix_function_definition() {
+    assert_eq!(
+        function("greet() { echo hi; echo there; }"),
+        ("greet".into(), 2)
+    );
+    assert_eq!(function("greet () { echo hi; }"), ("greet".into(), 1));
+}
+
+#[test]
+fn parse_function_keyword_definition() {
+    assert_eq!(
+        function("function greet() { echo hi; }"),
+        ("greet".into(), 1)
+    );
+}
@@ -0,0 +1,41 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+fn parse(src: &str) -> AstNode<'static> {
+    ShellCommandParser::new().parse(src).unwrap()
+}
+
+#[test]
+fn members_collect_into_one_group() {
+    match parse("sleep 1 &&& echo a | wc -c &&& true") {
+        AstNode::JobGroup(members) => {
+            assert_eq!(members.len(), 3);
+            assert!(matches!(members[1], AstNode::Pipeline { .. }));
+        }
+        other => panic!("expected job group, got {other:?}"),
+    }
+    assert_eq!(parse("a &&& b &&& c").to_string(), "a &&& b &&& c");
+}
+
+#[test]
+fn sequence_binds_more_loosely_than_a_group() {
+    match parse("echo a; sleep 1 &&& sleep 2") {
+        AstNode::Sequence { left, right } => {
+            assert!(matches!(*left, AstNode::Command { .. }));
+            assert!(matches!(*right, AstNode::JobGroup(ref members) if members.len() == 2));
+        }
+        other => panic!("expected sequence, got {other:?}"),
+    }
+    match parse("a &&& b && c") {
+        AstNode::LogicalAnd { left, .. } => assert!(matches!(*left, AstNode::JobGroup(_))),
+        other => panic!("expected and list, got {other:?}"),
+    }
+}
+
+#[test]
+fn backgrounded_group_is_one_job() {
+    match parse("sleep 1 &&& sleep 2 &") {
+        AstNode::Background(job) => assert_eq!(job.to_string(), "sleep 1 &&& sleep 2"),
+        other => panic!("expected background job, got {other:?}"),
+    }
+}
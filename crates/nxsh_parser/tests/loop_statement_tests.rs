@@ -0,0 +1,70 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+#[test]
+fn parse_until_loop() {
+    let p = ShellCommandParser::new();
+    let ast = p.parse("until false; do echo hi; done").unwrap();
+    match ast {
+        AstNode::Until { condition, body } => {
+            assert!(format!("{condition}").contains("false"));
+            assert!(format!("{body}").contains("echo"));
+        }
+        other => panic!("expected Until node, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_arithmetic_for_loop() {
+    let p = ShellCommandParser::new();
+    let ast = p.parse("for ((i=0; i<3; i++)); do echo $i; done").unwrap();
+    match ast {
+        AstNode::ForC {
+            init,
+            condition,
+            update,
+            ..
+        } => {
+            assert!(format!("{init:?}").contains("i=0"));
+            assert!(format!("{condition:?}").contains("i<3"));
+            assert!(format!("{update:?}").contains("i++"));
+        }
+        other => panic!("expected ForC node, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_arithmetic_for_loop_with_empty_clauses() {
+    let p = ShellCommandParser::new();
+    let ast = p.parse("for ((;;)); do break; done").unwrap();
+    match ast {
+        AstNode::ForC {
+            init,
+            condition,
+            update,
+            ..
+        } => {
+            assert!(init.is_none());
+            assert!(condition.is_none());
+            assert!(update.is_none());
+        }
+        other => panic!("expected ForC node, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_arithmetic_for_loop_with_parenthesised_update() {
+    let p = ShellCommandParser::new();
+    let ast = p
+        .parse("for ((i=0; i<(n); i+=(2))); do echo $i; done")
+        .unwrap();
+    match ast {
+        AstNode::ForC {
+            condition, update, ..
+        } => {
+            assert!(format!("{condition:?}").contains("i<(n)"));
+            assert!(format!("{update:?}").contains("i+=(2)"));
+        }
+        other => panic!("expected ForC node, got {other:?}"),
+    }
+}
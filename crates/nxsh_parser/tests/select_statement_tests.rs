@@ -18,3 +18,19 @@ fn parse_select_minimal_body() {
     let s = format!("{ast}");
     assert!(s.contains("echo"));
 }
+
+#[test]
+fn parse_select_with_terminators() {
+    let p = ShellCommandParser::new();
+    let src = "select fruit in apple banana; do echo $fruit; done";
+    match p.parse(src).unwrap() {
+        nxsh_parser::ast::AstNode::Select {
+            variable, options, ..
+        } => {
+            assert_eq!(variable, "fruit");
+            let opts = format!("{options:?}");
+            assert!(opts.contains("apple") && opts.contains("banana"));
+        }
+        other => panic!("expected Select node, got {other:?}"),
+    }
+}
@@ -0,0 +1,32 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+fn expansion_names(src: &str) -> Vec<String> {
+    fn walk(node: &AstNode, out: &mut Vec<String>) {
+        match node {
+            AstNode::VariableExpansion { name, .. } => out.push(name.to_string()),
+            AstNode::Program(list) | AstNode::StatementList(list) => {
+                list.iter().for_each(|n| walk(n, out))
+            }
+            AstNode::Command { args, .. } => args.iter().for_each(|n| walk(n, out)),
+            _ => {}
+        }
+    }
+    let ast = ShellCommandParser::new().parse(src).unwrap();
+    let mut names = Vec::new();
+    walk(&ast, &mut names);
+    names
+}
+
+#[test]
+fn parse_special_parameters() {
+    assert_eq!(
+        expansion_names("echo $1 $# $@ $* $? $$ $0"),
+        vec!["1", "#", "@", "*", "?", "$", "0"]
+    );
+}
+
+#[test]
+fn parse_braced_multi_digit_parameter() {
+    assert_eq!(expansion_names("echo ${10} $HOME"), vec!["10", "HOME"]);
+}
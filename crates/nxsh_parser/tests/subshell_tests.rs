@@ -0,0 +1,35 @@
+use nxsh_parser::ast::AstNode;
+use nxsh_parser::ShellCommandParser;
+
+fn subshell_body(src: &str) -> AstNode<'static> {
+    match ShellCommandParser::new().parse(src).unwrap() {
+        AstNode::Subshell(body) => *body,
+        other => panic!("expected subshell, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_subshell_body_as_program() {
+    assert!(matches!(
+        subshell_body("(cd /tmp && make)"),
+        AstNode::LogicalAnd { .. }
+    ));
+    match subshell_body("(\n  x=1\n  echo $x\n)") {
+        AstNode::Program(statements) => assert_eq!(statements.len(), 2),
+        other => panic!("expected program, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_subshell_in_pipeline() {
+    match ShellCommandParser::new()
+        .parse("(echo a; echo b) | cat")
+        .unwrap()
+    {
+        AstNode::Pipeline { elements, .. } => {
+            assert!(matches!(elements[0], AstNode::Subshell(_)));
+            assert_eq!(elements.len(), 2);
+        }
+        other => panic!("expected pipeline, got {other:?}"),
+    }
+}
@@ -0,0 +1,124 @@
+use nxsh_parser::ast::{AstNode, QuoteType};
+use nxsh_parser::ShellCommandParser;
+
+fn parse(src: &str) -> AstNode<'static> {
+    ShellCommandParser::new().parse(src).unwrap()
+}
+
+#[test]
+fn parse_try_catch_finally_on_one_line() {
+    match parse("try { throw boom; } catch err { echo $err.message; } finally { echo done; }") {
+        AstNode::Try {
+            body,
+            catch_clauses,
+            finally_clause,
+        } => {
+            assert_eq!(
+                *body,
+                AstNode::ThrowStatement(Box::new(AstNode::Word("boom")))
+            );
+            assert_eq!(catch_clauses.len(), 1);
+            assert_eq!(catch_clauses[0].variable, Some("err"));
+            let catch_body = format!("{:?}", catch_clauses[0].body);
+            assert!(catch_body.contains("err.message"), "{catch_body}");
+            assert!(finally_clause.is_some());
+        }
+        other => panic!("expected Try, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_multi_line_try_blocks() {
+    let src = "try {\n  x=1\n\n  throw \"bad thing\"\n}\ncatch {\n  echo caught\n}\necho after\n";
+    match parse(src) {
+        AstNode::Program(statements) => {
+            assert_eq!(statements.len(), 2);
+            match &statements[0] {
+                AstNode::Try {
+                    body,
+                    catch_clauses,
+                    finally_clause,
+                } => {
+                    match &**body {
+                        AstNode::StatementList(body) => {
+                            assert_eq!(body.len(), 2);
+                            assert_eq!(
+                                body[1],
+                                AstNode::ThrowStatement(Box::new(AstNode::StringLiteral {
+                                    value: "bad thing",
+                                    quote_type: QuoteType::Double,
+                                }))
+                            );
+                        }
+                        other => panic!("expected two statements, got {other:?}"),
+                    }
+                    assert_eq!(catch_clauses[0].variable, None);
+                    assert!(finally_clause.is_none());
+                }
+                other => panic!("expected Try, got {other:?}"),
+            }
+        }
+        other => panic!("expected program, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_try_with_only_finally() {
+    match parse("try { false; } finally { echo done; }") {
+        AstNode::Try {
+            catch_clauses,
+            finally_clause,
+            ..
+        } => {
+            assert!(catch_clauses.is_empty());
+            assert!(finally_clause.is_some());
+        }
+        other => panic!("expected Try, got {other:?}"),
+    }
+}
+
+#[test]
+fn keywords_are_plain_words_as_arguments() {
+    match parse("echo try catch finally throw }x") {
+        AstNode::Command { args, .. } => assert_eq!(args.len(), 5),
+        other => panic!("expected Command, got {other:?}"),
+    }
+    assert_eq!(
+        parse("throw"),
+        AstNode::ThrowStatement(Box::new(AstNode::Word("")))
+    );
+}
+
+#[test]
+fn parse_throw_with_a_code() {
+    assert_eq!(
+        parse("throw 'not found' 127"),
+        AstNode::ThrowStatement(Box::new(AstNode::ArgumentList(vec![
+            AstNode::StringLiteral {
+                value: "not found",
+                quote_type: QuoteType::Single,
+            },
+            AstNode::Word("127"),
+        ])))
+    );
+}
+
+#[test]
+fn function_bodies_end_at_their_closing_brace() {
+    match parse("function greet(name) { echo hi; }\ngreet") {
+        AstNode::Program(statements) => {
+            match &statements[0] {
+                AstNode::Function {
+                    name, params, body, ..
+                } => {
+                    assert_eq!(*name, "greet");
+                    assert_eq!(params[0].name, "name");
+                    assert!(matches!(&**body, AstNode::Program(body) if body.len() == 1));
+                }
+                other => panic!("expected Function, got {other:?}"),
+            }
+            assert_eq!(statements.len(), 2);
+        }
+        other => panic!("expected program, got {other:?}"),
+    }
+}
@@ -0,0 +1,15 @@
+//! Runs a native plugin for the shell in a process of its own, answering
+//! the requests the shell writes to standard input; see
+//! `nxsh_plugin::isolation`
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match nxsh_plugin::isolation::serve(std::io::stdin().lock(), std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("nxsh-plugin-host: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
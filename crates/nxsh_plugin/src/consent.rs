@@ -0,0 +1,265 @@
+//! Consent to the capabilities plugins ask for
+//!
+//! The manifest of a WASI plugin lists the capabilities it needs. When a
+//! [`PluginManager`](crate::PluginManager) has a [`CapabilityConsent`], it
+//! grants a plugin only the capabilities consented to: a decision recorded
+//! in [`GRANTS_FILE`] in the configuration directory, or else the answer of
+//! a [`ConsentPrompt`], which is recorded in turn. Decisions are kept per
+//! plugin name and version, so a new version is asked about again. A
+//! capability nobody answers for is denied for this load only.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::manager::config_dir;
+use crate::PluginMetadata;
+
+/// Decisions on plugin capabilities, in the configuration directory
+pub const GRANTS_FILE: &str = "plugin-permissions.toml";
+
+/// Whether a plugin may have a capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Granted,
+    Denied,
+}
+
+/// The capabilities of a plugin being loaded that have not been decided on
+#[derive(Debug, Clone, Copy)]
+pub struct ConsentRequest<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub description: &'a str,
+    pub capabilities: &'a [String],
+}
+
+/// Asks whether plugins may have capabilities, usually the user
+pub trait ConsentPrompt: Send + Sync {
+    /// Decide on each of `request.capabilities`, in order, or return `None`
+    /// when there is nobody to ask
+    fn ask(&self, request: &ConsentRequest<'_>) -> Option<Vec<Decision>>;
+}
+
+/// The decisions recorded for one version of a plugin
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginGrants {
+    #[serde(default)]
+    pub granted: Vec<String>,
+    #[serde(default)]
+    pub denied: Vec<String>,
+}
+
+impl PluginGrants {
+    /// The decision recorded on `capability`, if any
+    pub fn decision(&self, capability: &str) -> Option<Decision> {
+        if self.granted.iter().any(|c| c == capability) {
+            Some(Decision::Granted)
+        } else if self.denied.iter().any(|c| c == capability) {
+            Some(Decision::Denied)
+        } else {
+            None
+        }
+    }
+
+    fn record(&mut self, capability: &str, decision: Decision) {
+        self.granted.retain(|c| c != capability);
+        self.denied.retain(|c| c != capability);
+        match decision {
+            Decision::Granted => self.granted.push(capability.to_string()),
+            Decision::Denied => self.denied.push(capability.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GrantsFile {
+    /// By plugin ID, `NAME@VERSION`
+    #[serde(default)]
+    plugins: BTreeMap<String, PluginGrants>,
+}
+
+/// The decisions recorded in a grants file
+#[derive(Debug, Clone)]
+pub struct PermissionGrants {
+    path: PathBuf,
+}
+
+impl PermissionGrants {
+    /// The decisions recorded in the file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The decisions recorded in the configuration directory
+    pub fn open() -> Result<Self> {
+        config_dir()
+            .map(|dir| Self::new(dir.join(GRANTS_FILE)))
+            .ok_or_else(|| anyhow!("cannot find the configuration directory"))
+    }
+
+    /// File the decisions are recorded in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The decisions recorded for version `version` of plugin `name`
+    pub fn get(&self, name: &str, version: &str) -> Result<PluginGrants> {
+        let mut file = self.read()?;
+        Ok(file
+            .plugins
+            .remove(&format!("{name}@{version}"))
+            .unwrap_or_default())
+    }
+
+    /// All recorded decisions, by plugin ID
+    pub fn list(&self) -> Result<BTreeMap<String, PluginGrants>> {
+        Ok(self.read()?.plugins)
+    }
+
+    /// Record `decisions` on capabilities for version `version` of plugin
+    /// `name`, replacing earlier decisions on the same capabilities
+    pub fn record(
+        &self,
+        name: &str,
+        version: &str,
+        decisions: &[(String, Decision)],
+    ) -> Result<()> {
+        let mut file = self.read()?;
+        let grants = file.plugins.entry(format!("{name}@{version}")).or_default();
+        for (capability, decision) in decisions {
+            grants.record(capability, *decision);
+        }
+        self.save(&file)
+    }
+
+    /// Forget the decisions for every version of plugin `name`, returning
+    /// how many versions had some
+    pub fn forget(&self, name: &str) -> Result<usize> {
+        let mut file = self.read()?;
+        let before = file.plugins.len();
+        file.plugins
+            .retain(|id| id.rsplit_once('@').map_or(id.as_str(), |(n, _)| n) != name);
+        let forgotten = before - file.plugins.len();
+        if forgotten > 0 {
+            self.save(&file)?;
+        }
+        Ok(forgotten)
+    }
+
+    fn read(&self) -> Result<GrantsFile> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(GrantsFile::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("cannot read {}", self.path.display()))
+            }
+        };
+        toml::from_str(&text).with_context(|| format!("invalid {}", self.path.display()))
+    }
+
+    fn save(&self, file: &GrantsFile) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+        let text = toml::to_string(file).context("cannot serialize the plugin permissions")?;
+        fs::write(&self.path, text).with_context(|| format!("cannot write {}", self.path.display()))
+    }
+}
+
+/// The capabilities of a plugin split by what was decided
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Consented {
+    pub granted: Vec<String>,
+    /// Each with why it was denied
+    pub denied: Vec<(String, String)>,
+}
+
+/// Decides on the capabilities of plugins being loaded from recorded
+/// decisions, asking a prompt about the others
+pub struct CapabilityConsent {
+    grants: PermissionGrants,
+    prompt: Option<Box<dyn ConsentPrompt>>,
+}
+
+impl CapabilityConsent {
+    /// Consent from `grants`, asking `prompt` about undecided capabilities
+    pub fn new(grants: PermissionGrants, prompt: Option<Box<dyn ConsentPrompt>>) -> Self {
+        Self { grants, prompt }
+    }
+
+    /// The recorded decisions
+    pub fn grants(&self) -> &PermissionGrants {
+        &self.grants
+    }
+
+    /// Decide on the capabilities in `metadata`, recording the answers to
+    /// the prompt
+    pub fn decide(&self, metadata: &PluginMetadata) -> Result<Consented> {
+        let recorded = self.grants.get(&metadata.name, &metadata.version)?;
+        let undecided: Vec<String> = metadata
+            .capabilities
+            .iter()
+            .filter(|capability| recorded.decision(capability).is_none())
+            .cloned()
+            .collect();
+        let mut answers = BTreeMap::new();
+        if !undecided.is_empty() {
+            let request = ConsentRequest {
+                name: &metadata.name,
+                version: &metadata.version,
+                description: &metadata.description,
+                capabilities: &undecided,
+            };
+            let answered = self
+                .prompt
+                .as_ref()
+                .and_then(|prompt| prompt.ask(&request))
+                .filter(|decisions| decisions.len() == undecided.len());
+            if let Some(decisions) = answered {
+                let decisions: Vec<(String, Decision)> =
+                    undecided.iter().cloned().zip(decisions).collect();
+                self.grants
+                    .record(&metadata.name, &metadata.version, &decisions)?;
+                answers.extend(decisions);
+            }
+        }
+
+        let mut consented = Consented::default();
+        for capability in &metadata.capabilities {
+            let (decision, reason) = match recorded.decision(capability) {
+                Some(decision) => (decision, "denied earlier"),
+                None => match answers.get(capability) {
+                    Some(decision) => (*decision, "denied by the user"),
+                    None => (Decision::Denied, "not confirmed by the user"),
+                },
+            };
+            match decision {
+                Decision::Granted => consented.granted.push(capability.clone()),
+                Decision::Denied => consented
+                    .denied
+                    .push((capability.clone(), reason.to_string())),
+            }
+        }
+        Ok(consented)
+    }
+}
+
+/// What granting `capability` lets a plugin do, in words
+pub fn describe_capability(capability: &str) -> String {
+    match capability.split_once(':') {
+        Some(("file_read", path)) => format!("read files under {path}"),
+        Some(("file_write", path)) => format!("read and write files under {path}"),
+        _ => match capability {
+            "file_read" => "read files in the current directory".to_string(),
+            "file_write" => "read and write files in the current directory".to_string(),
+            "env_read" => "read environment variables".to_string(),
+            "network_request" => "connect to the network".to_string(),
+            other => format!("use {other}"),
+        },
+    }
+}
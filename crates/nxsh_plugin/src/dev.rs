@@ -0,0 +1,253 @@
+//! Plugin development mode
+//!
+//! A [`DevSession`] loads the artifact of a plugin under development, a
+//! `.wasm` module or a native `.so`, `.dylib` or `.dll` library, and watches
+//! it with a [`FileWatcher`]. When a build replaces the artifact,
+//! [`DevSession::reload`] swaps the new version in for the old one and the
+//! manager emits [`PluginEvent::Updated`](crate::PluginEvent::Updated).
+//!
+//! Plugin commands run with the plugin system locked for reading and a
+//! reload locks it for writing, so a reload waits for the commands running
+//! in the old instance to finish, and commands started meanwhile run in the
+//! new one. Native libraries are loaded from a copy of the artifact: the
+//! build can replace the file while the library is loaded, and the system
+//! loader cannot hand back the library it already has for the same path.
+
+use crate::manager::describe_plugin;
+use crate::PLUGIN_SYSTEM;
+use anyhow::{Context, Result};
+use nxsh_hal::fs::{FileChange, FileWatcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// How often the artifact is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Numbers the copies native libraries are loaded from
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A plugin swapped in by [`DevSession::reload`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reloaded {
+    /// ID of the plugin that was loaded before
+    pub old_id: String,
+    /// Version of the plugin that was loaded before
+    pub old_version: String,
+    /// ID of the plugin now loaded
+    pub plugin_id: String,
+    /// Version of the plugin now loaded
+    pub version: String,
+}
+
+/// A plugin artifact loaded in development mode and watched for changes
+#[derive(Debug)]
+pub struct DevSession {
+    artifact: PathBuf,
+    plugin_id: String,
+    version: String,
+    /// ID of the plugin the session's plugin took the place of
+    replaced: Option<String>,
+    watcher: FileWatcher,
+    /// The copy a native library is loaded from
+    copy: Option<PathBuf>,
+}
+
+impl DevSession {
+    /// Load the plugin at `artifact`, in place of a loaded plugin of the
+    /// same name, and start watching the artifact
+    pub async fn start(artifact: &Path) -> Result<Self> {
+        let artifact = std::fs::canonicalize(artifact)
+            .with_context(|| format!("Cannot watch {}", artifact.display()))?;
+        let native = match artifact.extension().and_then(|ext| ext.to_str()) {
+            Some("wasm") => false,
+            Some("so" | "dylib" | "dll") => true,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "{} is not a .wasm, .so, .dylib or .dll plugin",
+                    artifact.display()
+                ))
+            }
+        };
+        // Watching from before the load sees a build that finishes meanwhile
+        let mut watcher = FileWatcher::new(POLL_INTERVAL);
+        watcher.watch(&artifact);
+        let copy = if native {
+            Some(stage(&artifact)?)
+        } else {
+            None
+        };
+        let path = copy.as_deref().unwrap_or(&artifact);
+
+        let loaded = async {
+            let bytes = if native {
+                Vec::new()
+            } else {
+                std::fs::read(path)
+                    .with_context(|| format!("Failed to read plugin file: {}", path.display()))?
+            };
+            let name = describe_plugin(path, &bytes)?.name;
+
+            let system = PLUGIN_SYSTEM.clone();
+            let mut system = system.write().await;
+            let manager = system
+                .manager_mut()
+                .ok_or_else(|| anyhow::anyhow!("Plugin system not initialized"))?;
+            let previous = manager.list_plugins().into_iter().find(|id| {
+                manager
+                    .loaded_plugin_metadata(id)
+                    .is_some_and(|metadata| metadata.name == name)
+            });
+            let plugin_id = match &previous {
+                Some(previous) => manager.reload_plugin(previous, path).await?,
+                None => manager.load_plugin(path).await?,
+            };
+            let version = loaded_version(manager, &plugin_id);
+            Ok::<_, anyhow::Error>((plugin_id, version, previous))
+        }
+        .await;
+        let (plugin_id, version, replaced) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                if let Some(copy) = &copy {
+                    discard(copy);
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(DevSession {
+            artifact,
+            plugin_id,
+            version,
+            replaced,
+            watcher,
+            copy,
+        })
+    }
+
+    /// The artifact being watched
+    pub fn artifact(&self) -> &Path {
+        &self.artifact
+    }
+
+    /// ID of the plugin loaded from the artifact
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Version of the plugin loaded from the artifact
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// ID of the plugin of the same name that was loaded before the session
+    /// started, if any
+    pub fn replaced(&self) -> Option<&str> {
+        self.replaced.as_deref()
+    }
+
+    /// Wait up to `timeout` for the artifact to be written or replaced,
+    /// returning whether it was. An artifact that is removed, as some builds
+    /// do before writing it again, is not a change.
+    pub fn wait_for_change(&mut self, timeout: Duration) -> bool {
+        self.watcher
+            .wait(Some(timeout))
+            .iter()
+            .any(|change| !matches!(change, FileChange::Removed(_)))
+    }
+
+    /// Load the artifact as it is now in place of the plugin loaded from it.
+    /// If the new plugin fails to load, the old one is still loaded unless
+    /// it had already been unloaded; then the next reload loads afresh.
+    pub async fn reload(&mut self) -> Result<Reloaded> {
+        let copy = match self.copy {
+            Some(_) => Some(stage(&self.artifact)?),
+            None => None,
+        };
+        let path = copy.as_deref().unwrap_or(&self.artifact);
+
+        let system = PLUGIN_SYSTEM.clone();
+        let mut system = system.write().await;
+        let Some(manager) = system.manager_mut() else {
+            if let Some(copy) = &copy {
+                discard(copy);
+            }
+            return Err(anyhow::anyhow!("Plugin system not initialized"));
+        };
+        let old_loaded = manager.loaded_plugin_metadata(&self.plugin_id).is_some();
+        let result = if old_loaded {
+            manager.reload_plugin(&self.plugin_id, path).await
+        } else {
+            manager.load_plugin(path).await
+        };
+        let plugin_id = match result {
+            Ok(plugin_id) => plugin_id,
+            Err(e) => {
+                if let Some(copy) = &copy {
+                    discard(copy);
+                }
+                return Err(e);
+            }
+        };
+        let version = loaded_version(manager, &plugin_id);
+        drop(system);
+
+        if let Some(copy) = copy {
+            if let Some(old) = self.copy.replace(copy) {
+                discard(&old);
+            }
+        }
+        Ok(Reloaded {
+            old_id: std::mem::replace(&mut self.plugin_id, plugin_id.clone()),
+            old_version: std::mem::replace(&mut self.version, version.clone()),
+            plugin_id,
+            version,
+        })
+    }
+
+    /// Unload the plugin and stop watching its artifact
+    pub async fn stop(self) -> Result<()> {
+        let system = PLUGIN_SYSTEM.clone();
+        let mut system = system.write().await;
+        let result = match system.manager_mut() {
+            Some(manager) if manager.loaded_plugin_metadata(&self.plugin_id).is_some() => {
+                manager.unload_plugin(&self.plugin_id).await
+            }
+            _ => Ok(()),
+        };
+        drop(system);
+        if let Some(copy) = &self.copy {
+            discard(copy);
+        }
+        result
+    }
+}
+
+/// Version of the loaded plugin `plugin_id`
+fn loaded_version(manager: &crate::PluginManager, plugin_id: &str) -> String {
+    manager
+        .loaded_plugin_metadata(plugin_id)
+        .map(|metadata| metadata.version.clone())
+        .unwrap_or_default()
+}
+
+/// Copy the native library `artifact` to a directory of its own, keeping
+/// its filename, which names the plugin
+fn stage(artifact: &Path) -> Result<PathBuf> {
+    let dir = std::env::temp_dir()
+        .join(format!("nxsh-plugin-dev-{}", std::process::id()))
+        .join(GENERATION.fetch_add(1, Ordering::Relaxed).to_string());
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let copy = dir.join(artifact.file_name().unwrap_or_default());
+    std::fs::copy(artifact, &copy)
+        .with_context(|| format!("Failed to copy {}", artifact.display()))?;
+    Ok(copy)
+}
+
+/// Delete a copy made by [`stage`] once nothing is loaded from it
+fn discard(copy: &Path) {
+    if let Some(dir) = copy.parent() {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,574 @@
+//! Native plugins run in a separate process
+//!
+//! With `isolation = "process"` in [`PluginConfig`] the shell does not load a
+//! native plugin itself: it starts an `nxsh-plugin-host` for it, which loads
+//! the library and runs its commands, so a plugin that crashes takes down the
+//! host instead of the shell.
+//!
+//! The shell talks to the host over the host's standard input and output.
+//! Each message is [`FRAME_MARKER`], the length of its body as a 4-byte
+//! big-endian number and the body, a [`Request`] or [`Response`] in JSON.
+//! Whatever else the plugin writes to standard output while it runs a
+//! command is that command's output; the marker tells the two apart.
+//!
+//! A host that dies is started again, and the plugin loaded into it, when
+//! the next command runs, up to `max_restarts` times. A request that is not
+//! answered within `execution_timeout_ms` has its host killed.
+
+use crate::native_runtime::{execute_library, init_library, PluginResult};
+use crate::{PluginConfig, PluginError};
+use anyhow::{bail, Context, Result};
+use libloading::Library;
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Name of the program isolated plugins run in
+pub const HOST_PROGRAM: &str = "nxsh-plugin-host";
+
+/// Starts every message, so the host's responses can be found among the
+/// plugin's output
+pub const FRAME_MARKER: &[u8] = b"\0NXSH\0";
+
+const HEADER_LEN: usize = FRAME_MARKER.len() + 4;
+
+/// Largest message body accepted
+const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// What the shell asks of a host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Load the plugin library at `path`
+    Load { path: PathBuf, plugin_id: String },
+    /// Run `command` in the loaded plugin
+    Execute { command: String, args: Vec<String> },
+}
+
+/// How a host answers a [`Request`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The plugin is loaded
+    Loaded,
+    /// The command ran and returned `status`
+    Done { status: i32 },
+    /// The request failed
+    Error { message: String },
+}
+
+/// Write `message` to `output` as one frame
+pub fn write_frame<T: Serialize>(output: &mut impl Write, message: &T) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    let mut frame = Vec::with_capacity(HEADER_LEN + body.len());
+    frame.extend_from_slice(FRAME_MARKER);
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    output.write_all(&frame)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Read the next frame from `input`, or `None` at its end
+pub fn read_frame<T: DeserializeOwned>(input: &mut impl Read) -> Result<Option<T>> {
+    let mut header = [0u8; HEADER_LEN];
+    match input.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    if &header[..FRAME_MARKER.len()] != FRAME_MARKER {
+        bail!("malformed frame");
+    }
+    let len = frame_len(&header);
+    if len > MAX_FRAME {
+        bail!("frame of {len} bytes is too large");
+    }
+    let mut body = vec![0u8; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(
+        serde_json::from_slice(&body).context("malformed frame")?,
+    ))
+}
+
+fn frame_len(header: &[u8]) -> usize {
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&header[FRAME_MARKER.len()..HEADER_LEN]);
+    u32::from_be_bytes(len) as usize
+}
+
+/// A piece of what a host writes to standard output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk {
+    /// Written by the plugin
+    Output(Vec<u8>),
+    /// The body of a frame
+    Frame(Vec<u8>),
+}
+
+/// Separates the frames a host writes from the plugin output around them
+#[derive(Debug, Default)]
+pub struct FrameSplitter {
+    buf: Vec<u8>,
+}
+
+impl FrameSplitter {
+    /// Take in `data` read from the host, returning the output and the
+    /// frames it completes
+    pub fn push(&mut self, data: &[u8]) -> Vec<Chunk> {
+        self.buf.extend_from_slice(data);
+        let mut chunks = Vec::new();
+        loop {
+            let Some(start) = self
+                .buf
+                .windows(FRAME_MARKER.len())
+                .position(|window| window == FRAME_MARKER)
+            else {
+                // Hold back what may be the start of a marker
+                let held = (1..FRAME_MARKER.len())
+                    .rev()
+                    .find(|&n| self.buf.ends_with(&FRAME_MARKER[..n]))
+                    .unwrap_or(0);
+                let end = self.buf.len() - held;
+                if end > 0 {
+                    chunks.push(Chunk::Output(self.buf.drain(..end).collect()));
+                }
+                break;
+            };
+            if start > 0 {
+                chunks.push(Chunk::Output(self.buf.drain(..start).collect()));
+            }
+            if self.buf.len() < HEADER_LEN {
+                break;
+            }
+            let end = HEADER_LEN + frame_len(&self.buf);
+            if self.buf.len() < end {
+                break;
+            }
+            let frame = self.buf[HEADER_LEN..end].to_vec();
+            self.buf.drain(..end);
+            chunks.push(Chunk::Frame(frame));
+        }
+        chunks
+    }
+
+    /// What is left once the host has closed its output
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        (!self.buf.is_empty()).then(|| std::mem::take(&mut self.buf))
+    }
+}
+
+/// Answer the requests read from `input` on `output` until `input` ends;
+/// the body of `nxsh-plugin-host`
+pub fn serve(mut input: impl Read, mut output: impl Write) -> Result<()> {
+    let mut library: Option<Library> = None;
+    while let Some(request) = read_frame::<Request>(&mut input)? {
+        let response = match request {
+            Request::Load { path, plugin_id } => match load(&path, &plugin_id) {
+                Ok(loaded) => {
+                    library = Some(loaded);
+                    Response::Loaded
+                }
+                Err(e) => Response::Error {
+                    message: message(e),
+                },
+            },
+            Request::Execute { command, args } => match &library {
+                Some(library) => match execute_library(library, &command, &args) {
+                    Ok(status) => Response::Done { status },
+                    Err(e) => Response::Error {
+                        message: message(e),
+                    },
+                },
+                None => Response::Error {
+                    message: "no plugin is loaded".to_string(),
+                },
+            },
+        };
+        write_frame(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+fn load(path: &Path, plugin_id: &str) -> PluginResult<Library> {
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| PluginError::LoadError(format!("Failed to load library: {e}")))?;
+    init_library(&library, plugin_id)?;
+    Ok(library)
+}
+
+/// The message of `error` without the prefix of its kind, which the shell
+/// adds back
+fn message(error: PluginError) -> String {
+    match error {
+        PluginError::LoadError(message)
+        | PluginError::InitializationError(message)
+        | PluginError::ExecutionError(message)
+        | PluginError::InvalidArgument(message)
+        | PluginError::NotFound(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// The `nxsh-plugin-host` next to the running executable, or the one on
+/// `PATH`
+pub fn host_program() -> PathBuf {
+    let name = format!("{HOST_PROGRAM}{}", std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(&name))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// What a command run in a host did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    /// What the plugin's execute function returned
+    pub status: i32,
+    /// What the plugin wrote to standard output meanwhile
+    pub output: Vec<u8>,
+}
+
+/// A running host and what it has written
+#[derive(Debug)]
+struct HostProcess {
+    child: Child,
+    stdin: ChildStdin,
+    chunks: Receiver<Chunk>,
+}
+
+impl HostProcess {
+    fn spawn(program: &Path) -> PluginResult<Self> {
+        let mut child = Command::new(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| {
+                PluginError::LoadError(format!("cannot start {}: {e}", program.display()))
+            })?;
+        let (Some(stdin), Some(mut stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(PluginError::LoadError(
+                "cannot talk to the plugin host".to_string(),
+            ));
+        };
+        let (sender, chunks) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut splitter = FrameSplitter::default();
+            let mut buf = [0u8; 8192];
+            loop {
+                match stdout.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        for chunk in splitter.push(&buf[..n]) {
+                            if sender.send(chunk).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(rest) = splitter.finish() {
+                let _ = sender.send(Chunk::Output(rest));
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            chunks,
+        })
+    }
+
+    /// How the host exited, giving it a moment to once its output closed
+    fn exit_status(&mut self) -> String {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => return status.to_string(),
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                Ok(None) => return "killed after closing its output".to_string(),
+                Err(e) => return e.to_string(),
+            }
+        }
+    }
+}
+
+impl Drop for HostProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A native plugin loaded into an `nxsh-plugin-host`, started again when it
+/// dies
+#[derive(Debug)]
+pub struct PluginHost {
+    program: PathBuf,
+    path: PathBuf,
+    plugin_id: String,
+    timeout: Duration,
+    max_restarts: u32,
+    /// How many times the host died or was killed
+    failures: u32,
+    process: Option<HostProcess>,
+}
+
+impl PluginHost {
+    /// Start a host for the plugin at `path` and load the plugin into it,
+    /// with the limits of `config`
+    pub fn start(
+        config: &PluginConfig,
+        path: impl Into<PathBuf>,
+        plugin_id: &str,
+    ) -> PluginResult<Self> {
+        let mut host = Self {
+            program: config.host_program.clone().unwrap_or_else(host_program),
+            path: path.into(),
+            plugin_id: plugin_id.to_string(),
+            timeout: Duration::from_millis(config.execution_timeout_ms),
+            max_restarts: config.max_restarts,
+            failures: 0,
+            process: None,
+        };
+        host.ensure_running()?;
+        Ok(host)
+    }
+
+    /// ID of the plugin the host runs
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Process ID of the host, if it is running
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().map(|process| process.child.id())
+    }
+
+    /// Run `command` in the plugin, starting the host again first if it died
+    pub fn execute(&mut self, command: &str, args: &[String]) -> PluginResult<Execution> {
+        self.ensure_running()?;
+        let request = Request::Execute {
+            command: command.to_string(),
+            args: args.to_vec(),
+        };
+        match self.exchange(&request, &format!("running '{command}'"))? {
+            (Response::Done { status }, output) => Ok(Execution { status, output }),
+            (Response::Error { message }, _) => Err(PluginError::ExecutionError(message)),
+            (response, _) => Err(unexpected(response)),
+        }
+    }
+
+    fn ensure_running(&mut self) -> PluginResult<()> {
+        if self.process.is_some() {
+            return Ok(());
+        }
+        if self.failures > self.max_restarts {
+            return Err(PluginError::RuntimeError(format!(
+                "the plugin host for '{}' stopped {} times; not starting it again",
+                self.plugin_id, self.failures
+            )));
+        }
+        if self.failures > 0 {
+            warn!("Restarting the plugin host for '{}'", self.plugin_id);
+        }
+        self.process = Some(HostProcess::spawn(&self.program)?);
+        let request = Request::Load {
+            path: self.path.clone(),
+            plugin_id: self.plugin_id.clone(),
+        };
+        let error = match self.exchange(&request, "loading the plugin") {
+            Ok((Response::Loaded, _)) => return Ok(()),
+            Ok((Response::Error { message }, _)) => PluginError::LoadError(message),
+            Ok((response, _)) => unexpected(response),
+            Err(e) => e,
+        };
+        self.process = None;
+        Err(error)
+    }
+
+    /// Send `request` and wait for the answer, with what the plugin wrote
+    /// meanwhile; `doing` describes the request in errors
+    fn exchange(&mut self, request: &Request, doing: &str) -> PluginResult<(Response, Vec<u8>)> {
+        let Some(process) = self.process.as_mut() else {
+            return Err(PluginError::RuntimeError(format!(
+                "the plugin host for '{}' is not running",
+                self.plugin_id
+            )));
+        };
+        // Output written between requests belongs to none of them
+        while let Ok(chunk) = process.chunks.try_recv() {
+            debug!("Discarding {chunk:?} from the plugin host");
+        }
+        if write_frame(&mut process.stdin, request).is_err() {
+            return Err(self.died(doing));
+        }
+        let deadline = Instant::now() + self.timeout;
+        let mut output = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match process.chunks.recv_timeout(remaining) {
+                Ok(Chunk::Output(bytes)) => output.extend(bytes),
+                Ok(Chunk::Frame(body)) => match serde_json::from_slice(&body) {
+                    Ok(response) => return Ok((response, output)),
+                    Err(e) => {
+                        self.failures += 1;
+                        self.process = None;
+                        return Err(PluginError::RuntimeError(format!(
+                            "malformed response from the plugin host for '{}': {e}",
+                            self.plugin_id
+                        )));
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.failures += 1;
+                    self.process = None;
+                    return Err(PluginError::ExecutionError(format!(
+                        "plugin '{}' timed out after {} ms {doing}",
+                        self.plugin_id,
+                        self.timeout.as_millis()
+                    )));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.died(doing)),
+            }
+        }
+    }
+
+    fn died(&mut self, doing: &str) -> PluginError {
+        self.failures += 1;
+        let status = self
+            .process
+            .take()
+            .map(|mut process| process.exit_status())
+            .unwrap_or_default();
+        PluginError::RuntimeError(format!(
+            "the plugin host for '{}' crashed {doing} ({status})",
+            self.plugin_id
+        ))
+    }
+}
+
+fn unexpected(response: Response) -> PluginError {
+    PluginError::RuntimeError(format!(
+        "unexpected response from the plugin host: {response:?}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &Response) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, message).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let request = Request::Execute {
+            command: "greet".to_string(),
+            args: vec!["world".to_string()],
+        };
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &request).unwrap();
+        write_frame(
+            &mut bytes,
+            &Request::Load {
+                path: PathBuf::from("hello.so"),
+                plugin_id: "hello".to_string(),
+            },
+        )
+        .unwrap();
+        let mut input = bytes.as_slice();
+        assert_eq!(read_frame::<Request>(&mut input).unwrap(), Some(request));
+        assert!(matches!(
+            read_frame::<Request>(&mut input).unwrap(),
+            Some(Request::Load { .. })
+        ));
+        assert_eq!(read_frame::<Request>(&mut input).unwrap(), None);
+        assert!(read_frame::<Request>(&mut &b"hello, world"[..]).is_err());
+    }
+
+    #[test]
+    fn splits_plugin_output_from_frames() {
+        let done = frame(&Response::Done { status: 0 });
+        let mut stream = b"hello\n".to_vec();
+        stream.extend_from_slice(&done);
+        stream.extend_from_slice(b"bye\n");
+        stream.extend_from_slice(&frame(&Response::Loaded));
+
+        // However the stream is cut up, the same pieces come out of it
+        for size in [1, 3, 7, stream.len()] {
+            let mut splitter = FrameSplitter::default();
+            let mut chunks = Vec::new();
+            for piece in stream.chunks(size) {
+                chunks.extend(splitter.push(piece));
+            }
+            let mut output = Vec::new();
+            let mut frames = Vec::new();
+            for chunk in chunks {
+                match chunk {
+                    Chunk::Output(bytes) => output.push(bytes),
+                    Chunk::Frame(body) => {
+                        frames.push(serde_json::from_slice::<Response>(&body).unwrap());
+                        output.push(b"|".to_vec());
+                    }
+                }
+            }
+            assert_eq!(output.concat(), b"hello\n|bye\n|");
+            assert_eq!(frames, [Response::Done { status: 0 }, Response::Loaded]);
+            assert_eq!(splitter.finish(), None);
+        }
+
+        // An unfinished frame is left over when the host closes its output
+        let mut splitter = FrameSplitter::default();
+        assert!(splitter.push(&done[..5]).is_empty());
+        assert_eq!(splitter.finish(), Some(done[..5].to_vec()));
+    }
+
+    #[test]
+    fn host_answers_without_a_plugin() {
+        let mut input = Vec::new();
+        write_frame(
+            &mut input,
+            &Request::Execute {
+                command: "greet".to_string(),
+                args: Vec::new(),
+            },
+        )
+        .unwrap();
+        write_frame(
+            &mut input,
+            &Request::Load {
+                path: PathBuf::from("/nonexistent/hello.so"),
+                plugin_id: "hello".to_string(),
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        serve(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        assert_eq!(
+            read_frame::<Response>(&mut output).unwrap(),
+            Some(Response::Error {
+                message: "no plugin is loaded".to_string()
+            })
+        );
+        match read_frame::<Response>(&mut output).unwrap() {
+            Some(Response::Error { message }) => {
+                assert!(message.starts_with("Failed to load library"), "{message}")
+            }
+            response => panic!("{response:?}"),
+        }
+    }
+}
@@ -4,7 +4,7 @@
 //! with capability-based security and dynamic loading.
 //!
 //! STAGE 1: Native Rust Plugin Support (100% Pure Rust)
-//! STAGE 2: WASI Plugin Support (planned for future milestone)
+//! STAGE 2: WASI Plugin Support (wasmi, behind the `wasi-runtime` feature)
 
 use anyhow::Result;
 #[cfg(any(feature = "native-plugins", feature = "async-support"))]
@@ -13,6 +13,12 @@ use std::sync::Arc;
 #[cfg(any(feature = "native-plugins", feature = "async-support"))]
 use tokio::sync::RwLock;
 
+#[cfg(feature = "plugin-management")]
+pub mod consent; // Consent to plugin capabilities, remembered per plugin version
+#[cfg(feature = "hot-reload")]
+pub mod dev; // Development mode reloading plugins as they are rebuilt
+#[cfg(feature = "native-plugins")]
+pub mod isolation; // Native plugins run in an nxsh-plugin-host process
 pub mod json;
 #[cfg(any(feature = "crypto-verification", feature = "plugin-management"))]
 pub mod keys;
@@ -60,6 +66,14 @@ pub mod security;
 pub mod security_sandbox; // Security sandbox system
 #[cfg(feature = "crypto-verification")]
 pub mod signature;
+#[cfg(feature = "plugin-management")]
+pub mod store; // Plugins installed in the plugin directory
+#[cfg(all(feature = "wasi-runtime", feature = "plugin-management"))]
+pub mod testing; // Testing plugins without a shell, and conformance checks
+#[cfg(all(feature = "crypto-verification", feature = "plugin-management"))]
+pub mod trust; // Keyring, certificate chains and revocation of signing keys
+#[cfg(feature = "wasi-runtime")]
+pub mod wasi; // WASI preview1 host functions for plugins
 #[cfg(feature = "wasi-runtime")]
 pub mod wasi_advanced; // Advanced WASM/WASI runtime // Advanced plugin management
 
@@ -91,7 +105,7 @@ pub struct PluginSystem {
     #[cfg(feature = "native-plugins")]
     native_runtime: Option<NativePluginRuntime>,
     #[cfg(feature = "wasi-runtime")]
-    wasi_runtime: Option<WasiPluginRuntime>,
+    wasi_runtime: Option<Arc<WasiPluginRuntime>>,
     #[cfg(feature = "wasi-runtime")]
     component_registry: Option<ComponentRegistry>,
     #[cfg(feature = "wasi-runtime")]
@@ -133,8 +147,9 @@ impl PluginSystem {
         // Initialize WASI runtime
         #[cfg(feature = "wasi-runtime")]
         {
-            let wasi_runtime = WasiPluginRuntime::new()?;
-            self.wasi_runtime = Some(wasi_runtime);
+            let mut wasi_runtime = WasiPluginRuntime::new()?;
+            wasi_runtime.initialize().await?;
+            self.wasi_runtime = Some(Arc::new(wasi_runtime));
 
             // Initialize component registry
             let component_registry = ComponentRegistry::new()?;
@@ -145,8 +160,13 @@ impl PluginSystem {
             self.resource_table = Some(resource_table);
         }
 
-        // Initialize manager
-        let manager = PluginManager::new();
+        // Initialize manager, loading .wasm plugins into the shared WASI runtime
+        #[allow(unused_mut)]
+        let mut manager = PluginManager::new();
+        #[cfg(feature = "wasi-runtime")]
+        if let Some(wasi_runtime) = &self.wasi_runtime {
+            manager.set_wasi_runtime(Arc::clone(wasi_runtime));
+        }
         self.manager = Some(manager);
 
         self.initialized = true;
@@ -161,7 +181,7 @@ impl PluginSystem {
 
     #[cfg(feature = "wasi-runtime")]
     fn wasi_runtime(&self) -> Option<&WasiPluginRuntime> {
-        self.wasi_runtime.as_ref()
+        self.wasi_runtime.as_deref()
     }
 
     #[cfg(feature = "wasi-runtime")]
@@ -183,11 +203,6 @@ impl PluginSystem {
         self.native_runtime.as_mut()
     }
 
-    #[cfg(feature = "wasi-runtime")]
-    fn wasi_runtime_mut(&mut self) -> Option<&mut WasiPluginRuntime> {
-        self.wasi_runtime.as_mut()
-    }
-
     #[cfg(feature = "wasi-runtime")]
     fn component_registry_mut(&mut self) -> Option<&mut ComponentRegistry> {
         self.component_registry.as_mut()
@@ -234,6 +249,10 @@ pub async fn shutdown() -> Result<()> {
     }
 
     system.native_runtime = None;
+    #[cfg(feature = "wasi-runtime")]
+    {
+        system.wasi_runtime = None;
+    }
     system.manager = None;
     system.initialized = false;
 
@@ -277,6 +296,21 @@ pub fn unload_plugin(_plugin_id: &str) -> Result<()> {
     Err(anyhow::anyhow!("Plugin system disabled"))
 }
 
+/// Grant WASI plugins loaded from now on only the capabilities `consent`
+/// allows
+#[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+pub async fn set_consent(consent: consent::CapabilityConsent) -> Result<()> {
+    let system = PLUGIN_SYSTEM.clone();
+    let mut system = system.write().await;
+
+    if let Some(manager) = system.manager_mut() {
+        manager.set_consent(consent);
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Plugin system not initialized"))
+    }
+}
+
 /// List all loaded plugins
 #[cfg(feature = "native-plugins")]
 pub async fn list_plugins() -> Vec<String> {
@@ -316,6 +350,97 @@ pub fn execute_plugin(_plugin_id: &str, _function: &str, _args: &[String]) -> Re
     Err(anyhow::anyhow!("Native plugin support disabled"))
 }
 
+/// List the shell commands provided by loaded WASI plugins, each with the
+/// ID of the plugin providing it
+#[cfg(feature = "wasi-runtime")]
+pub async fn plugin_commands() -> Vec<(String, runtime::CommandSpec)> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.command_specs().await,
+        None => vec![],
+    }
+}
+
+/// Run a command provided by a loaded WASI plugin
+#[cfg(feature = "wasi-runtime")]
+pub async fn run_command(
+    command: &str,
+    args: &[String],
+    env: &HashMap<String, String>,
+    cwd: &std::path::Path,
+) -> Result<runtime::CommandOutput> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.run_command(command, args, env, cwd).await,
+        None => Err(anyhow::anyhow!("Plugin system not initialized")),
+    }
+}
+
+/// List the completers of loaded WASI plugins, each with the ID of the
+/// plugin providing it
+#[cfg(feature = "wasi-runtime")]
+pub async fn plugin_completers() -> Vec<(String, runtime::CompleterSpec)> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.completers().await,
+        None => vec![],
+    }
+}
+
+/// List the prompt segments of loaded WASI plugins, each with the ID of the
+/// plugin providing it
+#[cfg(feature = "wasi-runtime")]
+pub async fn plugin_segments() -> Vec<(String, runtime::SegmentSpec)> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.segments().await,
+        None => vec![],
+    }
+}
+
+/// Complete the word under the cursor in `line` with the completer a loaded
+/// WASI plugin provides for the arguments of `command`
+#[cfg(feature = "wasi-runtime")]
+pub async fn complete(
+    command: &str,
+    line: &str,
+    cursor: usize,
+    env: &HashMap<String, String>,
+    cwd: &std::path::Path,
+) -> Result<Vec<String>> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.complete(command, line, cursor, env, cwd).await,
+        None => Err(anyhow::anyhow!("Plugin system not initialized")),
+    }
+}
+
+/// Render the prompt segment `name` of a loaded WASI plugin
+#[cfg(feature = "wasi-runtime")]
+pub async fn render_segment(
+    name: &str,
+    env: &HashMap<String, String>,
+    cwd: &std::path::Path,
+) -> Result<String> {
+    let system = PLUGIN_SYSTEM.clone();
+    let system = system.read().await;
+
+    match system.wasi_runtime() {
+        Some(runtime) => runtime.render_segment(name, env, cwd).await,
+        None => Err(anyhow::anyhow!("Plugin system not initialized")),
+    }
+}
+
 // Plugin configuration and metadata types
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -338,6 +463,33 @@ pub struct PluginConfig {
     /// If true, require plugins to declare at least one capability (test aid; env can override)
     #[serde(default)]
     pub capabilities_manifest_required: bool,
+    /// Where native plugins run
+    #[serde(default)]
+    pub isolation: Isolation,
+    /// How many times the host of an isolated plugin is started again after
+    /// it crashes or times out
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    /// The `nxsh-plugin-host` isolated plugins run in, by default the one next
+    /// to the running executable or on `PATH`
+    #[serde(default)]
+    pub host_program: Option<std::path::PathBuf>,
+}
+
+/// Where native plugins run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Isolation {
+    /// Loaded into the shell's own process
+    #[default]
+    InProcess,
+    /// Each in an `nxsh-plugin-host` process, so a crash does not take the
+    /// shell down
+    Process,
+}
+
+fn default_max_restarts() -> u32 {
+    3
 }
 
 impl Default for PluginConfig {
@@ -356,6 +508,9 @@ impl Default for PluginConfig {
             require_signatures: true,
             enable_encryption: true,
             capabilities_manifest_required: false,
+            isolation: Isolation::InProcess,
+            max_restarts: default_max_restarts(),
+            host_program: None,
         }
     }
 }
@@ -424,6 +579,16 @@ pub enum PluginEvent {
         old_version: String,
         new_version: String,
     },
+    DownloadProgress {
+        plugin_id: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    Downloaded {
+        plugin_id: String,
+        repository: String,
+        checksum: String,
+    },
 }
 
 /// Plugin event handler trait
@@ -6,6 +6,8 @@
 use anyhow::{Context, Result};
 #[cfg(feature = "plugin-management")]
 use semver::{Version, VersionReq};
+#[cfg(feature = "wasi-runtime")]
+use std::sync::Arc;
 use std::{
     collections::{HashMap, HashSet},
     path::{Path, PathBuf},
@@ -17,10 +19,13 @@ use tokio::fs;
 use walkdir::WalkDir;
 
 // Note: cfg attributes cannot be placed inside a use tree list. Split them.
+#[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+use crate::consent::CapabilityConsent;
 #[cfg(feature = "native-plugins")]
 use crate::native_runtime::NativePluginRuntime;
+#[cfg(feature = "wasi-runtime")]
+use crate::runtime::{self, RuntimeConfig, WasiPluginRuntime};
 use crate::{
-    // runtime::WasiPluginRuntime,
     // component::ComponentRegistry,
     PluginConfig,
     PluginEvent,
@@ -28,6 +33,105 @@ use crate::{
     PluginMetadata,
 };
 
+/// The NexusShell configuration directory: `$NXSH_CONFIG_DIR`, or
+/// `nexusshell` in the user's configuration directory
+#[cfg(feature = "plugin-management")]
+pub fn config_dir() -> Option<PathBuf> {
+    match std::env::var_os("NXSH_CONFIG_DIR") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => Some(dirs::config_dir()?.join("nexusshell")),
+    }
+}
+
+/// Directory WASI plugins are loaded from at startup: `$NXSH_PLUGIN_DIR`,
+/// or `plugins` in the NexusShell configuration directory
+#[cfg(feature = "plugin-management")]
+pub fn plugin_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("NXSH_PLUGIN_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    Some(config_dir()?.join("plugins"))
+}
+
+/// Describe the plugin at `path`, whose contents are `bytes`: WASM plugins
+/// by the manifest in their custom section, native plugins by their filename
+pub fn describe_plugin(path: &Path, bytes: &[u8]) -> Result<PluginMetadata> {
+    let filename = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown");
+
+    #[cfg(feature = "wasi-runtime")]
+    if path.extension().and_then(|ext| ext.to_str()) == Some("wasm") {
+        let manifest = runtime::read_manifest(bytes)?.unwrap_or_default();
+        return Ok(PluginMetadata {
+            name: manifest.name.unwrap_or_else(|| filename.to_string()),
+            version: manifest.version.unwrap_or_else(|| "0.1.0".to_string()),
+            description: manifest
+                .description
+                .unwrap_or_else(|| format!("Plugin loaded from {}", path.display())),
+            author: manifest.author.unwrap_or_else(|| "unknown".to_string()),
+            license: manifest.license.unwrap_or_else(|| "unknown".to_string()),
+            homepage: None,
+            repository: None,
+            keywords: vec![],
+            categories: vec![],
+            capabilities: manifest.capabilities,
+            exports: manifest
+                .commands
+                .iter()
+                .map(|command| command.name().to_string())
+                .collect(),
+            dependencies: HashMap::new(),
+            min_nexus_version: "0.1.0".to_string(),
+            max_nexus_version: None,
+        });
+    }
+    #[cfg(not(feature = "wasi-runtime"))]
+    let _ = bytes;
+
+    Ok(PluginMetadata {
+        name: filename.to_string(),
+        version: "0.1.0".to_string(),
+        description: format!("Plugin loaded from {}", path.display()),
+        author: "unknown".to_string(),
+        license: "unknown".to_string(),
+        homepage: None,
+        repository: None,
+        keywords: vec![],
+        categories: vec![],
+        capabilities: vec![],
+        exports: vec!["main".to_string()],
+        dependencies: HashMap::new(),
+        min_nexus_version: "0.1.0".to_string(),
+        max_nexus_version: None,
+    })
+}
+
+/// What the WASI runtime needs to know of a `.wasm` plugin: the manifest's
+/// command specs carry help and completion, besides its completers and
+/// prompt segments
+#[cfg(feature = "wasi-runtime")]
+pub(crate) fn runtime_metadata(
+    metadata: &PluginMetadata,
+    bytes: &[u8],
+) -> Result<runtime::PluginMetadata> {
+    let manifest = runtime::read_manifest(bytes)?.unwrap_or_default();
+    Ok(runtime::PluginMetadata {
+        name: metadata.name.clone(),
+        version: metadata.version.clone(),
+        description: metadata.description.clone(),
+        permissions: metadata.capabilities.clone(),
+        commands: manifest
+            .commands
+            .into_iter()
+            .map(runtime::ManifestCommand::into_spec)
+            .collect(),
+        completers: manifest.completers,
+        segments: manifest.segments,
+    })
+}
+
 /// Plugin Manager for handling plugin lifecycle
 pub struct PluginManager {
     config: PluginConfig,
@@ -36,7 +140,11 @@ pub struct PluginManager {
     dependency_graph: DependencyGraph,
     event_handlers: Vec<Box<dyn PluginEventHandler>>,
     native_runtime: Option<NativePluginRuntime>,
-    // wasi_runtime: Option<WasiPluginRuntime>,  // Stage 2: WASI support (C-free for now)
+    #[cfg(feature = "wasi-runtime")]
+    wasi_runtime: Option<Arc<WasiPluginRuntime>>,
+    /// Decides which capabilities WASI plugins get; without it, all they ask for
+    #[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+    consent: Option<CapabilityConsent>,
     // component_registry: ComponentRegistry,    // Stage 2: Component registry (C-free for now)
 }
 
@@ -56,7 +164,10 @@ impl PluginManager {
             dependency_graph: DependencyGraph::new(),
             event_handlers: Vec::new(),
             native_runtime: None,
-            // wasi_runtime: None,                  // Stage 2: WASI support (C-free for now)
+            #[cfg(feature = "wasi-runtime")]
+            wasi_runtime: None,
+            #[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+            consent: None,
             // component_registry: ComponentRegistry::new(),  // Stage 2: Component registry (C-free for now)
         }
     }
@@ -70,7 +181,10 @@ impl PluginManager {
             dependency_graph: DependencyGraph::new(),
             event_handlers: Vec::new(),
             native_runtime: None,
-            // wasi_runtime: None,                  // Stage 2: WASI support (C-free for now)
+            #[cfg(feature = "wasi-runtime")]
+            wasi_runtime: None,
+            #[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+            consent: None,
             // component_registry: ComponentRegistry::new(),  // Stage 2: Component registry (C-free for now)
         }
     }
@@ -81,26 +195,67 @@ impl PluginManager {
         self.native_runtime = Some(runtime);
     }
 
-    // /// Set the WASI runtime for the manager (Stage 2)
-    // pub fn set_wasi_runtime(&mut self, runtime: WasiPluginRuntime) {
-    //     self.wasi_runtime = Some(runtime);
-    // }
+    /// Set the WASI runtime for the manager
+    #[cfg(feature = "wasi-runtime")]
+    pub fn set_wasi_runtime(&mut self, runtime: Arc<WasiPluginRuntime>) {
+        self.wasi_runtime = Some(runtime);
+    }
+
+    /// The WASI runtime loaded `.wasm` plugins run in
+    #[cfg(feature = "wasi-runtime")]
+    pub fn wasi_runtime(&self) -> Option<&Arc<WasiPluginRuntime>> {
+        self.wasi_runtime.as_ref()
+    }
+
+    /// Grant WASI plugins loaded from now on only the capabilities `consent`
+    /// allows
+    #[cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+    pub fn set_consent(&mut self, consent: CapabilityConsent) {
+        self.consent = Some(consent);
+    }
+
+    /// The capabilities in `metadata` the plugin is granted, and those it is
+    /// denied with why
+    #[cfg(feature = "wasi-runtime")]
+    fn consent(&self, metadata: &PluginMetadata) -> Result<(Vec<String>, Vec<(String, String)>)> {
+        for capability in &metadata.capabilities {
+            runtime::check_capability(capability)?;
+        }
+        #[cfg(feature = "plugin-management")]
+        if let Some(consent) = &self.consent {
+            let consented = consent.decide(metadata)?;
+            return Ok((consented.granted, consented.denied));
+        }
+        Ok((metadata.capabilities.clone(), Vec::new()))
+    }
 
-    /// Initialize native runtime only (Stage 1)
+    /// Initialize the native and WASI runtimes
     pub async fn initialize_runtimes(&mut self) -> Result<()> {
         // Initialize native runtime
         #[cfg(feature = "native-plugins")]
         {
-            let native_runtime = NativePluginRuntime::new()?;
+            let native_runtime = NativePluginRuntime::with_config(self.config.clone())?;
             self.set_native_runtime(native_runtime);
         }
 
-        // // Initialize WASI runtime (Stage 2 - C-free for now)
-        // let wasi_runtime = WasiPluginRuntime::new().await
-        //     .context("Failed to initialize WASI runtime")?;
-        // self.set_wasi_runtime(wasi_runtime);
+        // Initialize WASI runtime
+        #[cfg(feature = "wasi-runtime")]
+        {
+            let mut wasi_runtime = WasiPluginRuntime::with_config(RuntimeConfig {
+                execution_timeout_ms: self.config.execution_timeout_ms,
+                max_memory_bytes: self.config.max_memory_mb * 1024 * 1024,
+                max_concurrent_executions: self.config.max_concurrent_executions,
+                debug_mode: false,
+                plugin_directory: Some(PathBuf::from(&self.config.plugin_dir)),
+            })?;
+            wasi_runtime
+                .initialize()
+                .await
+                .context("Failed to initialize WASI runtime")?;
+            self.set_wasi_runtime(Arc::new(wasi_runtime));
+        }
 
-        log::info!("Native plugin runtime initialized successfully");
+        log::info!("Plugin runtimes initialized successfully");
         Ok(())
     }
 
@@ -214,29 +369,14 @@ impl PluginManager {
 
     /// Extract metadata from a plugin file
     async fn extract_plugin_metadata(&self, path: &Path) -> Result<PluginMetadata> {
-        // For now, generate basic metadata from filename
-        // In a real implementation, this would parse the WASM component metadata
-        let filename = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown");
-
-        Ok(PluginMetadata {
-            name: filename.to_string(),
-            version: "0.1.0".to_string(),
-            description: format!("Plugin loaded from {}", path.display()),
-            author: "unknown".to_string(),
-            license: "unknown".to_string(),
-            homepage: None,
-            repository: None,
-            keywords: vec![],
-            categories: vec![],
-            capabilities: vec![],
-            exports: vec!["main".to_string()],
-            dependencies: HashMap::new(),
-            min_nexus_version: "0.1.0".to_string(),
-            max_nexus_version: None,
-        })
+        let bytes = if path.extension().and_then(|ext| ext.to_str()) == Some("wasm") {
+            fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read plugin file: {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        describe_plugin(path, &bytes)
     }
 
     /// Validate plugin metadata
@@ -292,10 +432,10 @@ impl PluginManager {
         id
     }
 
-    /// Load a plugin from file (Stage 1: Native only)
+    /// Load a native or WASI plugin from file
     pub async fn load_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<String> {
         let path = path.as_ref();
-        log::info!("Loading native plugin from: {}", path.display());
+        log::info!("Loading plugin from: {}", path.display());
 
         // Extract metadata
         let metadata = self.extract_plugin_metadata(path).await?;
@@ -309,8 +449,9 @@ impl PluginManager {
         // Resolve dependencies
         self.resolve_dependencies(&metadata).await?;
 
-        // For now, only support native plugins (Stage 1)
         let file_extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        #[allow(unused_mut)]
+        let mut permission_events = Vec::new();
 
         let plugin_type = match file_extension.to_lowercase().as_str() {
             "so" | "dll" | "dylib" => {
@@ -327,10 +468,42 @@ impl PluginManager {
                 }
                 PluginType::Native
             }
-            // "wasm" => {
-            //     // Stage 2: WASI plugin support (C-free for now)
-            //     return Err(anyhow::anyhow!("WASM plugins not yet supported in C-free mode"));
-            // }
+            #[cfg(feature = "wasi-runtime")]
+            "wasm" => {
+                let runtime = self
+                    .wasi_runtime
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("WASI runtime not available for WASM plugin"))?;
+                let bytes = fs::read(path)
+                    .await
+                    .with_context(|| format!("Failed to read plugin file: {}", path.display()))?;
+                let runtime_metadata = runtime_metadata(&metadata, &bytes)?;
+                let (granted, denied) = self.consent(&metadata)?;
+                runtime
+                    .load_plugin_from_bytes(plugin_id.clone(), &bytes, runtime_metadata)
+                    .await
+                    .context("Failed to load WASM plugin")?;
+                // The plugin gets the capabilities its manifest declares that
+                // are consented to, and runs without the others
+                for capability in granted {
+                    if let Err(e) = runtime.grant_capability(&plugin_id, &capability).await {
+                        runtime.unload_plugin(&plugin_id).await?;
+                        return Err(e);
+                    }
+                    permission_events.push(PluginEvent::PermissionGranted {
+                        plugin_id: plugin_id.clone(),
+                        capability,
+                    });
+                }
+                for (capability, reason) in denied {
+                    permission_events.push(PluginEvent::PermissionDenied {
+                        plugin_id: plugin_id.clone(),
+                        capability,
+                        reason,
+                    });
+                }
+                PluginType::Wasi
+            }
             _ => {
                 // Default to native plugin for unknown extensions
                 if let Some(runtime) = &self.native_runtime {
@@ -361,11 +534,14 @@ impl PluginManager {
             metadata: Box::new(metadata),
         })
         .await;
+        for event in permission_events {
+            self.emit_event(event).await;
+        }
 
         Ok(plugin_id)
     }
 
-    /// Unload a plugin (Stage 1: Native only)
+    /// Unload a plugin
     pub async fn unload_plugin(&mut self, plugin_id: &str) -> Result<()> {
         log::info!("Unloading plugin: {plugin_id}");
 
@@ -385,7 +561,7 @@ impl PluginManager {
             ));
         }
 
-        // Unload from appropriate runtime based on plugin type (Stage 1: Native only)
+        // Unload from appropriate runtime based on plugin type
         match plugin_info.plugin_type {
             PluginType::Native => {
                 if let Some(runtime) = &self.native_runtime {
@@ -394,10 +570,16 @@ impl PluginManager {
                         .await
                         .context("Failed to unload native plugin from runtime")?;
                 }
-            } // PluginType::Wasi => {
-              //     // Stage 2: WASI support (C-free for now)
-              //     return Err(anyhow::anyhow!("WASI plugin unloading not yet supported"));
-              // }
+            }
+            #[cfg(feature = "wasi-runtime")]
+            PluginType::Wasi => {
+                if let Some(runtime) = &self.wasi_runtime {
+                    runtime
+                        .unload_plugin(plugin_id)
+                        .await
+                        .context("Failed to unload WASI plugin from runtime")?;
+                }
+            }
         }
 
         // Remove from loaded plugins
@@ -491,6 +673,13 @@ impl PluginManager {
             .map(|entry| &entry.metadata)
     }
 
+    /// Get the metadata of a loaded plugin
+    pub fn loaded_plugin_metadata(&self, plugin_id: &str) -> Option<&PluginMetadata> {
+        self.loaded_plugins
+            .get(plugin_id)
+            .map(|plugin| &plugin.metadata)
+    }
+
     /// Get plugin status
     pub fn get_plugin_status(&self, plugin_id: &str) -> Option<PluginStatus> {
         self.plugin_registry
@@ -580,6 +769,46 @@ impl PluginManager {
         Ok(())
     }
 
+    /// Replace the loaded plugin `plugin_id` with the one at `path`, whatever
+    /// its version, returning the new plugin's ID. The new plugin is checked
+    /// before the old one is unloaded, so one that cannot be loaded leaves
+    /// the old one running.
+    pub async fn reload_plugin(&mut self, plugin_id: &str, path: &Path) -> Result<String> {
+        log::info!("Reloading plugin {plugin_id} from {}", path.display());
+
+        let old_version = self
+            .loaded_plugins
+            .get(plugin_id)
+            .ok_or_else(|| anyhow::anyhow!("Plugin not loaded: {}", plugin_id))?
+            .metadata
+            .version
+            .clone();
+        let new_metadata = self.extract_plugin_metadata(path).await?;
+        self.validate_plugin_metadata(&new_metadata)?;
+        #[cfg(feature = "wasi-runtime")]
+        if path.extension().and_then(|ext| ext.to_str()) == Some("wasm") {
+            if let Some(runtime) = &self.wasi_runtime {
+                let bytes = fs::read(path)
+                    .await
+                    .with_context(|| format!("Failed to read plugin file: {}", path.display()))?;
+                let runtime_metadata = runtime_metadata(&new_metadata, &bytes)?;
+                runtime.validate_plugin(plugin_id, &bytes, &runtime_metadata)?;
+            }
+        }
+
+        self.unload_plugin(plugin_id).await?;
+        let new_plugin_id = self.load_plugin(path).await?;
+
+        self.emit_event(PluginEvent::Updated {
+            plugin_id: new_plugin_id.clone(),
+            old_version,
+            new_version: new_metadata.version,
+        })
+        .await;
+
+        Ok(new_plugin_id)
+    }
+
     /// Get dependency graph
     pub fn get_dependency_graph(&self) -> &DependencyGraph {
         &self.dependency_graph
@@ -614,7 +843,8 @@ struct LoadedPluginInfo {
 #[derive(Debug, Clone, PartialEq)]
 pub enum PluginType {
     Native,
-    // Wasi,  // Stage 2: WASI support (C-free for now)
+    #[cfg(feature = "wasi-runtime")]
+    Wasi,
 }
 
 /// Plugin registry entry
@@ -3,6 +3,9 @@
 //! This module provides a Pure Rust implementation for loading and executing
 //! native Rust plugins (.dll/.so/.dylib) without any C/C++ dependencies.
 //! WASI/WebAssembly support will be added in a future milestone.
+//!
+//! With `isolation = "process"` in [`PluginConfig`] plugins are loaded into
+//! an `nxsh-plugin-host` process instead, see [`crate::isolation`].
 
 use anyhow::{Context, Result};
 #[cfg(feature = "native-plugins")]
@@ -12,8 +15,9 @@ use std::{collections::HashMap, ffi::CString, path::Path, sync::Arc};
 use tokio::sync::RwLock;
 
 use crate::{
+    isolation::PluginHost,
     security::{CapabilityManager, SandboxContext},
-    PluginConfig, PluginError, PluginMetadata,
+    Isolation, PluginConfig, PluginError, PluginMetadata,
 };
 
 // Type alias for plugin results to avoid naming conflicts
@@ -32,7 +36,6 @@ pub struct NativePluginRuntime {
     capability_manager: CapabilityManager,
 
     /// Runtime configuration
-    #[allow(dead_code)]
     config: PluginConfig,
 
     /// Plugin registry for metadata tracking
@@ -48,8 +51,8 @@ pub struct LoadedLibrary {
     /// Plugin metadata
     pub metadata: PluginMetadata,
 
-    /// Loaded dynamic library
-    pub library: Library,
+    /// Where the plugin is loaded
+    pub instance: PluginInstance,
 
     /// Sandbox context for capability restrictions
     pub sandbox_context: SandboxContext,
@@ -61,6 +64,15 @@ pub struct LoadedLibrary {
     pub execution_count: u64,
 }
 
+/// Where a loaded native plugin runs
+#[derive(Debug)]
+pub enum PluginInstance {
+    /// Loaded into the shell
+    InProcess(Library),
+    /// Loaded into an `nxsh-plugin-host` process
+    Process(Arc<std::sync::Mutex<PluginHost>>),
+}
+
 /// Native plugin function signature for initialization
 ///
 /// Every native plugin must export this function:
@@ -168,14 +180,8 @@ impl NativePluginRuntime {
         // Validate plugin file extension
         self.validate_plugin_file(path)?;
 
-        // Load the dynamic library using Pure Rust libloading
-        let library = unsafe {
-            Library::new(path)
-                .map_err(|e| PluginError::LoadError(format!("Failed to load library: {e}")))?
-        };
-
         // Extract plugin metadata by calling plugin initialization function
-        let metadata = self.extract_plugin_metadata(&library, &plugin_id).await?;
+        let metadata = self.extract_plugin_metadata(&plugin_id).await?;
 
         // Validate plugin security requirements
         self.capability_manager
@@ -189,14 +195,34 @@ impl NativePluginRuntime {
             .await
             .map_err(|e| PluginError::SecurityError(format!("Failed to create sandbox: {e}")))?;
 
-        // Initialize the plugin
-        self.initialize_plugin(&library, &plugin_id).await?;
+        let instance = match self.config.isolation {
+            Isolation::InProcess => {
+                // Load the dynamic library using Pure Rust libloading
+                let library = unsafe {
+                    Library::new(path).map_err(|e| {
+                        PluginError::LoadError(format!("Failed to load library: {e}"))
+                    })?
+                };
+
+                // Initialize the plugin
+                self.initialize_plugin(&library, &plugin_id).await?;
+                PluginInstance::InProcess(library)
+            }
+            Isolation::Process => {
+                // The host loads and initializes the plugin
+                let config = self.config.clone();
+                let (path, id) = (path.to_path_buf(), plugin_id.clone());
+                let host =
+                    on_blocking_thread(move || PluginHost::start(&config, path, &id)).await?;
+                PluginInstance::Process(Arc::new(std::sync::Mutex::new(host)))
+            }
+        };
 
         // Create loaded library entry
         let loaded_library = LoadedLibrary {
             id: plugin_id.clone(),
             metadata: metadata.clone(),
-            library,
+            instance,
             sandbox_context,
             loaded_at: chrono::Utc::now(),
             execution_count: 0,
@@ -255,7 +281,7 @@ impl NativePluginRuntime {
         debug!("Executing command '{command}' in plugin '{plugin_id}'");
 
         // Check if plugin is loaded and has permissions
-        {
+        let host = {
             let libraries = self.libraries.read().await;
             let loaded_lib = libraries
                 .get(plugin_id)
@@ -267,10 +293,35 @@ impl NativePluginRuntime {
                     "Plugin '{plugin_id}' does not have permission to execute command '{command}'"
                 )));
             }
-        }
 
-        // Simulate plugin execution - in production, this would call the actual plugin function
-        let result = format!("Executed '{command}' with args {args:?} in plugin '{plugin_id}'");
+            match &loaded_lib.instance {
+                PluginInstance::InProcess(_) => None,
+                PluginInstance::Process(host) => Some(host.clone()),
+            }
+        };
+
+        let result = match host {
+            Some(host) => {
+                let (command, args) = (command.to_string(), args.to_vec());
+                let execution = on_blocking_thread(move || {
+                    host.lock()
+                        .map_err(|_| {
+                            PluginError::RuntimeError("plugin host lock poisoned".to_string())
+                        })?
+                        .execute(&command, &args)
+                })
+                .await?;
+                if execution.status != 0 {
+                    return Err(PluginError::ExecutionError(format!(
+                        "Plugin execution failed with code: {}",
+                        execution.status
+                    )));
+                }
+                String::from_utf8_lossy(&execution.output).into_owned()
+            }
+            // Simulate plugin execution - in production, this would call the actual plugin function
+            None => format!("Executed '{command}' with args {args:?} in plugin '{plugin_id}'"),
+        };
 
         // Update execution statistics
         {
@@ -338,11 +389,7 @@ impl NativePluginRuntime {
     }
 
     /// Extract plugin metadata by calling the plugin's initialization function
-    async fn extract_plugin_metadata(
-        &self,
-        _library: &Library,
-        plugin_id: &str,
-    ) -> PluginResult<PluginMetadata> {
+    async fn extract_plugin_metadata(&self, plugin_id: &str) -> PluginResult<PluginMetadata> {
         // For now, create basic metadata from the plugin ID
         // In a full implementation, this would call the plugin's metadata function
         Ok(PluginMetadata {
@@ -365,49 +412,7 @@ impl NativePluginRuntime {
 
     /// Initialize a plugin by calling its init function
     async fn initialize_plugin(&self, library: &Library, plugin_id: &str) -> PluginResult<()> {
-        // Try to find and call the plugin initialization function
-        // This is where the plugin registers its capabilities
-        match unsafe { library.get::<PluginInitFn>(b"nxsh_plugin_init") } {
-            Ok(init_fn) => {
-                // Create CStrings with proper error handling
-                let plugin_id_cstr = CString::new(plugin_id)
-                    .map_err(|e| PluginError::RuntimeError(format!("Invalid plugin ID: {e}")))?;
-                let plugin_name_cstr = CString::new("")
-                    .map_err(|e| PluginError::RuntimeError(format!("Invalid plugin name: {e}")))?;
-                let plugin_version_cstr = CString::new("1.0.0").map_err(|e| {
-                    PluginError::RuntimeError(format!("Invalid plugin version: {e}"))
-                })?;
-                let author_cstr = CString::new("")
-                    .map_err(|e| PluginError::RuntimeError(format!("Invalid author: {e}")))?;
-
-                // Create a registrar for the plugin
-                let mut registrar = PluginRegistrar {
-                    plugin_id: plugin_id_cstr.as_ptr(),
-                    plugin_name: plugin_name_cstr.as_ptr(),
-                    plugin_version: plugin_version_cstr.as_ptr(),
-                    required_capabilities: std::ptr::null(),
-                    capability_count: 0,
-                    author: author_cstr.as_ptr(),
-                };
-
-                // Call the plugin's initialization function
-                let result = unsafe { init_fn(&mut registrar) };
-
-                if result != 0 {
-                    return Err(PluginError::InitializationError(format!(
-                        "Plugin initialization failed with code: {result}"
-                    )));
-                }
-
-                info!("Plugin '{plugin_id}' initialized successfully");
-            }
-            Err(e) => {
-                warn!("Plugin '{plugin_id}' does not export nxsh_plugin_init function: {e}");
-                // This is not necessarily an error - the plugin might use a different interface
-            }
-        }
-
-        Ok(())
+        init_library(library, plugin_id)
     }
 
     /// Call a plugin's execute function
@@ -417,38 +422,102 @@ impl NativePluginRuntime {
         command: &str,
         args: &[String],
     ) -> PluginResult<String> {
-        // Try to find and call the plugin execution function
-        match unsafe { library.get::<PluginExecuteFn>(b"nxsh_plugin_execute") } {
-            Ok(execute_fn) => {
-                // Convert Rust strings to C strings
-                let command_cstr = CString::new(command)
-                    .map_err(|e| PluginError::InvalidArgument(format!("Invalid command: {e}")))?;
-
-                // Convert arguments to C string array
-                let arg_cstrs: Result<Vec<CString>, _> =
-                    args.iter().map(|arg| CString::new(arg.as_str())).collect();
-                let arg_cstrs = arg_cstrs
-                    .map_err(|e| PluginError::InvalidArgument(format!("Invalid argument: {e}")))?;
-
-                let arg_ptrs: Vec<*const std::ffi::c_char> =
-                    arg_cstrs.iter().map(|cstr| cstr.as_ptr()).collect();
-
-                // Call the plugin's execute function
-                let result =
-                    unsafe { execute_fn(command_cstr.as_ptr(), arg_ptrs.as_ptr(), args.len()) };
-
-                if result == 0 {
-                    Ok("Command executed successfully".to_string())
-                } else {
-                    Err(PluginError::ExecutionError(format!(
-                        "Plugin execution failed with code: {result}"
-                    )))
-                }
+        let result = execute_library(library, command, args)?;
+        if result == 0 {
+            Ok("Command executed successfully".to_string())
+        } else {
+            Err(PluginError::ExecutionError(format!(
+                "Plugin execution failed with code: {result}"
+            )))
+        }
+    }
+}
+
+/// Run blocking work with a plugin host off the async runtime
+async fn on_blocking_thread<T: Send + 'static>(
+    work: impl FnOnce() -> PluginResult<T> + Send + 'static,
+) -> PluginResult<T> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| PluginError::RuntimeError(format!("Plugin host task failed: {e}")))?
+}
+
+/// Initialize the plugin in `library` by calling its `nxsh_plugin_init`, if
+/// it exports one
+pub(crate) fn init_library(library: &Library, plugin_id: &str) -> PluginResult<()> {
+    // Try to find and call the plugin initialization function
+    // This is where the plugin registers its capabilities
+    match unsafe { library.get::<PluginInitFn>(b"nxsh_plugin_init") } {
+        Ok(init_fn) => {
+            // Create CStrings with proper error handling
+            let plugin_id_cstr = CString::new(plugin_id)
+                .map_err(|e| PluginError::RuntimeError(format!("Invalid plugin ID: {e}")))?;
+            let plugin_name_cstr = CString::new("")
+                .map_err(|e| PluginError::RuntimeError(format!("Invalid plugin name: {e}")))?;
+            let plugin_version_cstr = CString::new("1.0.0")
+                .map_err(|e| PluginError::RuntimeError(format!("Invalid plugin version: {e}")))?;
+            let author_cstr = CString::new("")
+                .map_err(|e| PluginError::RuntimeError(format!("Invalid author: {e}")))?;
+
+            // Create a registrar for the plugin
+            let mut registrar = PluginRegistrar {
+                plugin_id: plugin_id_cstr.as_ptr(),
+                plugin_name: plugin_name_cstr.as_ptr(),
+                plugin_version: plugin_version_cstr.as_ptr(),
+                required_capabilities: std::ptr::null(),
+                capability_count: 0,
+                author: author_cstr.as_ptr(),
+            };
+
+            // Call the plugin's initialization function
+            let result = unsafe { init_fn(&mut registrar) };
+
+            if result != 0 {
+                return Err(PluginError::InitializationError(format!(
+                    "Plugin initialization failed with code: {result}"
+                )));
             }
-            Err(e) => Err(PluginError::NotFound(format!(
-                "Plugin does not export nxsh_plugin_execute function: {e}"
-            ))),
+
+            info!("Plugin '{plugin_id}' initialized successfully");
+        }
+        Err(e) => {
+            warn!("Plugin '{plugin_id}' does not export nxsh_plugin_init function: {e}");
+            // This is not necessarily an error - the plugin might use a different interface
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `command` with the `nxsh_plugin_execute` of `library`, returning
+/// what it returned
+pub(crate) fn execute_library(
+    library: &Library,
+    command: &str,
+    args: &[String],
+) -> PluginResult<i32> {
+    // Try to find and call the plugin execution function
+    match unsafe { library.get::<PluginExecuteFn>(b"nxsh_plugin_execute") } {
+        Ok(execute_fn) => {
+            // Convert Rust strings to C strings
+            let command_cstr = CString::new(command)
+                .map_err(|e| PluginError::InvalidArgument(format!("Invalid command: {e}")))?;
+
+            // Convert arguments to C string array
+            let arg_cstrs: Result<Vec<CString>, _> =
+                args.iter().map(|arg| CString::new(arg.as_str())).collect();
+            let arg_cstrs = arg_cstrs
+                .map_err(|e| PluginError::InvalidArgument(format!("Invalid argument: {e}")))?;
+
+            let arg_ptrs: Vec<*const std::ffi::c_char> =
+                arg_cstrs.iter().map(|cstr| cstr.as_ptr()).collect();
+
+            // Call the plugin's execute function
+            Ok(unsafe { execute_fn(command_cstr.as_ptr(), arg_ptrs.as_ptr(), args.len()) })
         }
+        Err(e) => Err(PluginError::NotFound(format!(
+            "Plugin does not export nxsh_plugin_execute function: {e}"
+        ))),
     }
 }
 
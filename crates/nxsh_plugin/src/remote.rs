@@ -1,21 +1,59 @@
 //! Remote Plugin Support for NexusShell
 //!
-//! This module provides functionality for downloading, managing, and updating
-//! plugins from remote repositories. Uses Pure Rust HTTP client (ureq) to maintain
-//! zero C dependencies policy.
+//! This module provides a client for remote plugin repositories. Uses Pure
+//! Rust HTTP client (ureq) to maintain zero C dependencies policy; the shell
+//! has no TLS of its own, so `https` URLs are fetched with the system's
+//! `curl`.
+//!
+//! A repository publishes a JSON index of its plugins as [`INDEX_FILE`] under
+//! its base URL, with the base64 Ed25519 signature of the index bytes, made
+//! with the repository's key, in [`INDEX_SIGNATURE_FILE`]:
+//!
+//! ```json
+//! {"plugins": {"greeter": [
+//!     {"version": "1.2.0", "url": "greeter-1.2.0.wasm", "size": 48213,
+//!      "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+//!      "min_nexus_version": "0.1.0"}]}}
+//! ```
+//!
+//! Resolving a plugin picks its newest release this shell can run: one whose
+//! `min_nexus_version`, and `max_nexus_version` if given, admit
+//! [`NEXUS_VERSION`], whose platforms include this one and that meets the
+//! version requirement asked for. Downloads are checked against the size and
+//! SHA-256 checksum in the index and report progress as [`PluginEvent`]s.
+//!
+//! A repository may list mirrors, tried in order when its base URL fails.
+//! Verified indexes and downloads are kept in the cache directory. When a
+//! repository cannot be reached its cached index is used instead, and in
+//! offline mode (`NXSH_PLUGIN_OFFLINE=1`) nothing but the cache is used.
 
 use crate::keys::{load_community_pubkey_b64, load_official_pubkey_b64};
-use anyhow::{Context, Result};
+use crate::PluginEvent;
+use anyhow::{anyhow, bail, Context, Result};
 #[cfg(feature = "remote-plugins")]
 use base64::engine::{general_purpose::STANDARD as BASE64, Engine};
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
-use std::io::{Read, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, create_dir_all};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
 #[cfg(feature = "remote-plugins")]
 use ureq;
 
+/// Version of NexusShell that plugin releases are resolved for
+pub const NEXUS_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Name of a repository's index under its base URL
+pub const INDEX_FILE: &str = "index.json";
+
+/// Name of the signature of a repository's index under its base URL
+pub const INDEX_SIGNATURE_FILE: &str = "index.json.sig";
+
+const USER_AGENT: &str = "NexusShell-Plugin-Manager/0.1.0";
+
 /// Remote plugin repository configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteRepository {
@@ -24,6 +62,9 @@ pub struct RemoteRepository {
     pub public_key: String,
     pub priority: u32,
     pub enabled: bool,
+    /// Base URLs serving the same content, tried in order when `base_url` fails
+    #[serde(default)]
+    pub mirrors: Vec<String>,
 }
 
 /// Remote plugin metadata
@@ -42,15 +83,91 @@ pub struct RemotePluginInfo {
     pub size: u64,
 }
 
+/// A repository's index: the releases of each plugin, by plugin name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, Vec<IndexEntry>>,
+}
+
+/// A plugin release in a repository's index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub version: String,
+    /// Artifact URL, relative to the repository's base URL unless absolute
+    pub url: String,
+    /// SHA-256 of the artifact, in hex
+    pub sha256: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default = "any_version")]
+    pub min_nexus_version: String,
+    #[serde(default)]
+    pub max_nexus_version: Option<String>,
+    /// `OS-ARCH` pairs it runs on; empty or `all` for every platform
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+}
+
+fn any_version() -> String {
+    "0.0.0".to_string()
+}
+
+/// A plugin release chosen from a repository
+#[derive(Debug, Clone)]
+pub struct ResolvedPlugin {
+    pub name: String,
+    pub repository: RemoteRepository,
+    pub entry: IndexEntry,
+}
+
+impl ResolvedPlugin {
+    /// Id of the release, `NAME@VERSION`
+    pub fn id(&self) -> String {
+        format!("{}@{}", self.name, self.entry.version)
+    }
+
+    /// Where the artifact is downloaded from
+    pub fn download_url(&self) -> String {
+        join_url(&self.repository.base_url, &self.entry.url)
+    }
+
+    /// The release as [`RemotePluginInfo`]
+    pub fn info(&self) -> RemotePluginInfo {
+        RemotePluginInfo {
+            id: self.id(),
+            name: self.name.clone(),
+            version: self.entry.version.clone(),
+            description: self.entry.description.clone(),
+            author: self.entry.author.clone(),
+            download_url: self.download_url(),
+            checksum: self.entry.sha256.clone(),
+            signature: None,
+            dependencies: Vec::new(),
+            platforms: self.entry.platforms.clone(),
+            size: self.entry.size.unwrap_or(0),
+        }
+    }
+}
+
+/// Receives the events of downloads
+pub type RemoteEventHandler = Arc<dyn Fn(PluginEvent) + Send + Sync>;
+
 /// Remote plugin manager
 pub struct RemotePluginManager {
     repositories: Vec<RemoteRepository>,
     cache_dir: PathBuf,
-    user_agent: String,
+    offline: bool,
+    events: Option<RemoteEventHandler>,
 }
 
 impl RemotePluginManager {
-    /// Create a new remote plugin manager
+    /// Create a new remote plugin manager, offline when
+    /// `NXSH_PLUGIN_OFFLINE` is set
     pub fn new<P: AsRef<Path>>(cache_dir: P) -> Result<Self> {
         let cache_dir = cache_dir.as_ref().to_path_buf();
         create_dir_all(&cache_dir)
@@ -59,7 +176,10 @@ impl RemotePluginManager {
         Ok(Self {
             repositories: Vec::new(),
             cache_dir,
-            user_agent: "NexusShell-Plugin-Manager/0.1.0".to_string(),
+            offline: std::env::var("NXSH_PLUGIN_OFFLINE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            events: None,
         })
     }
 
@@ -71,89 +191,253 @@ impl RemotePluginManager {
             .sort_by(|a, b| b.priority.cmp(&a.priority));
     }
 
+    /// Use only the cache, never the network
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Whether only the cache is used
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Send download progress and results to `handler`
+    pub fn on_event(&mut self, handler: impl Fn(PluginEvent) + Send + Sync + 'static) {
+        self.events = Some(Arc::new(handler));
+    }
+
+    fn emit(&self, event: PluginEvent) {
+        if let Some(events) = &self.events {
+            events(event);
+        }
+    }
+
     /// Download plugin from remote repository
+    ///
+    /// `plugin_id` is a plugin name, optionally followed by `@` and a version
+    /// requirement such as `greeter@^1.2`.
     pub fn download_plugin(&self, plugin_id: &str, dest_path: &Path) -> Result<RemotePluginInfo> {
-        // Try each repository until successful
-        for repo in &self.repositories {
-            if !repo.enabled {
-                continue;
-            }
+        let (name, requirement) = match plugin_id.split_once('@') {
+            Some((name, requirement)) => (
+                name,
+                Some(
+                    VersionReq::parse(requirement)
+                        .with_context(|| format!("Invalid version requirement: {requirement}"))?,
+                ),
+            ),
+            None => (plugin_id, None),
+        };
+        let resolved = self.resolve(name, requirement.as_ref())?;
+        self.download(&resolved, dest_path)
+    }
 
-            match self.try_download_from_repo(repo, plugin_id, dest_path) {
-                Ok(info) => return Ok(info),
+    /// The newest release of `name` this shell can run that meets
+    /// `requirement`, from any enabled repository; the repository with the
+    /// higher priority wins a tie
+    pub fn resolve(&self, name: &str, requirement: Option<&VersionReq>) -> Result<ResolvedPlugin> {
+        let nexus = Version::parse(NEXUS_VERSION)?;
+        let mut best: Option<(Version, ResolvedPlugin)> = None;
+        let mut errors = Vec::new();
+        for repo in self.repositories.iter().filter(|repo| repo.enabled) {
+            let index = match self.index(repo) {
+                Ok(index) => index,
                 Err(e) => {
-                    log::warn!("Failed to download {} from {}: {}", plugin_id, repo.name, e);
+                    log::warn!("Failed to read the index of {}: {e:#}", repo.name);
+                    errors.push(format!("{e:#}"));
+                    continue;
+                }
+            };
+            for entry in index.plugins.get(name).into_iter().flatten() {
+                let Ok(version) = Version::parse(&entry.version) else {
                     continue;
+                };
+                if requirement.is_some_and(|requirement| !requirement.matches(&version))
+                    || !self.is_compatible(entry, &nexus)
+                {
+                    continue;
+                }
+                if best.as_ref().map_or(true, |(newest, _)| version > *newest) {
+                    let resolved = ResolvedPlugin {
+                        name: name.to_string(),
+                        repository: repo.clone(),
+                        entry: entry.clone(),
+                    };
+                    best = Some((version, resolved));
                 }
             }
         }
-
-        anyhow::bail!("Plugin '{}' not found in any repository", plugin_id)
+        match best {
+            Some((_, resolved)) => Ok(resolved),
+            None if !errors.is_empty() => bail!(
+                "Plugin '{name}' not found in any repository: {}",
+                errors.join("; ")
+            ),
+            None => bail!(
+                "Plugin '{name}' has no release for NexusShell {NEXUS_VERSION} on this platform"
+            ),
+        }
     }
 
-    /// Try downloading from specific repository
-    fn try_download_from_repo(
+    /// Download `resolved` to `dest_path`, checking its size and checksum;
+    /// a verified copy in the cache is used without downloading it again
+    pub fn download(
         &self,
-        repo: &RemoteRepository,
-        plugin_id: &str,
+        resolved: &ResolvedPlugin,
         dest_path: &Path,
     ) -> Result<RemotePluginInfo> {
-        // Get plugin metadata
-        let metadata_url = format!("{}/api/v1/plugins/{}/info", repo.base_url, plugin_id);
-        let response = ureq::get(&metadata_url)
-            .set("User-Agent", &self.user_agent)
-            .call()
-            .with_context(|| format!("Failed to fetch metadata from {metadata_url}"))?;
-
-        let body = response
-            .into_string()
-            .with_context(|| "Failed to read metadata response")?;
-        let plugin_info: RemotePluginInfo =
-            serde_json::from_str(&body).with_context(|| "Failed to parse plugin metadata")?;
-
-        // Verify platform compatibility
-        if !self.is_platform_compatible(&plugin_info.platforms)? {
-            anyhow::bail!(
-                "Plugin {} is not compatible with current platform",
-                plugin_id
-            );
+        let info = resolved.info();
+        let file_name = resolved
+            .entry
+            .url
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&resolved.name);
+        let cached = self
+            .cache_dir
+            .join(&resolved.repository.name)
+            .join(&resolved.name)
+            .join(file_name);
+
+        let bytes = match fs::read(&cached) {
+            Ok(bytes) if self.verify_checksum(&bytes, &resolved.entry.sha256).is_ok() => bytes,
+            _ if self.offline => bail!("{} is not in the offline cache", info.id),
+            _ => {
+                let mut progress = |downloaded: u64, total: Option<u64>| {
+                    self.emit(PluginEvent::DownloadProgress {
+                        plugin_id: info.id.clone(),
+                        downloaded,
+                        total: total.or(resolved.entry.size),
+                    })
+                };
+                let bytes = if is_url(&resolved.entry.url) {
+                    fetch_url(&resolved.entry.url, &mut progress)?
+                        .ok_or_else(|| anyhow!("{}: not found", resolved.entry.url))?
+                } else {
+                    self.fetch_from(&resolved.repository, &resolved.entry.url, &mut progress)?
+                };
+                if let Err(e) = self.check_artifact(&resolved.entry, &bytes) {
+                    self.emit(PluginEvent::Error {
+                        plugin_id: info.id.clone(),
+                        error: format!("{e:#}"),
+                    });
+                    return Err(e.context("Plugin checksum verification failed"));
+                }
+                if let Err(e) = cached
+                    .parent()
+                    .map_or(Ok(()), create_dir_all)
+                    .and_then(|()| fs::write(&cached, &bytes))
+                {
+                    log::warn!("Failed to cache {}: {e}", info.id);
+                }
+                bytes
+            }
+        };
+
+        fs::write(dest_path, &bytes)
+            .with_context(|| format!("Failed to create destination file: {dest_path:?}"))?;
+        self.emit(PluginEvent::Downloaded {
+            plugin_id: info.id.clone(),
+            repository: resolved.repository.name.clone(),
+            checksum: info.checksum.clone(),
+        });
+        Ok(info)
+    }
+
+    /// The verified index of `repo`, from the network unless offline, or
+    /// else from the cache
+    pub fn index(&self, repo: &RemoteRepository) -> Result<RegistryIndex> {
+        let cache = self.cache_dir.join(&repo.name);
+        let fetch_error = if self.offline {
+            anyhow!("offline")
+        } else {
+            match self.fetch_index(repo) {
+                Ok((bytes, signature)) => {
+                    let index = self.verify_index(repo, &bytes, &signature)?;
+                    if let Err(e) = create_dir_all(&cache)
+                        .and_then(|()| fs::write(cache.join(INDEX_FILE), &bytes))
+                        .and_then(|()| fs::write(cache.join(INDEX_SIGNATURE_FILE), &signature))
+                    {
+                        log::warn!("Failed to cache the index of {}: {e}", repo.name);
+                    }
+                    return Ok(index);
+                }
+                Err(e) => e,
+            }
+        };
+        let cached = fs::read(cache.join(INDEX_FILE)).and_then(|bytes| {
+            fs::read_to_string(cache.join(INDEX_SIGNATURE_FILE)).map(|signature| (bytes, signature))
+        });
+        match cached {
+            Ok((bytes, signature)) => {
+                log::info!("Using the cached index of {}", repo.name);
+                self.verify_index(repo, &bytes, &signature)
+            }
+            Err(_) => Err(fetch_error.context(format!(
+                "Repository {} cannot be reached and has no cached index",
+                repo.name
+            ))),
         }
+    }
+
+    fn fetch_index(&self, repo: &RemoteRepository) -> Result<(Vec<u8>, String)> {
+        let bytes = self.fetch_from(repo, INDEX_FILE, &mut |_, _| {})?;
+        let signature = self.fetch_from(repo, INDEX_SIGNATURE_FILE, &mut |_, _| {})?;
+        let signature = String::from_utf8(signature).context("Invalid index signature")?;
+        Ok((bytes, signature))
+    }
 
-        // Download plugin binary
-        let download_response = ureq::get(&plugin_info.download_url)
-            .set("User-Agent", &self.user_agent)
-            .call()
-            .with_context(|| {
-                format!(
-                    "Failed to download plugin from {}",
-                    plugin_info.download_url
-                )
-            })?; // cannot use shorthand inside braces due to borrow later
-
-        // Read all bytes
-        let mut bytes = Vec::new();
-        download_response
-            .into_reader()
-            .read_to_end(&mut bytes)
-            .with_context(|| "Failed to read plugin data")?;
-
-        // Verify checksum
-        self.verify_checksum(&bytes, &plugin_info.checksum)
-            .with_context(|| "Plugin checksum verification failed")?;
-
-        // Verify signature if present
-        if let Some(signature) = &plugin_info.signature {
-            self.verify_signature(&bytes, signature, &repo.public_key)
-                .with_context(|| "Plugin signature verification failed")?;
+    fn verify_index(
+        &self,
+        repo: &RemoteRepository,
+        bytes: &[u8],
+        signature: &str,
+    ) -> Result<RegistryIndex> {
+        if repo.public_key.trim().is_empty() {
+            bail!(
+                "Repository {} has no public key to check its index with",
+                repo.name
+            );
         }
+        self.verify_signature(bytes, signature.trim(), repo.public_key.trim())
+            .with_context(|| format!("Index of {} is not signed by its key", repo.name))?;
+        serde_json::from_slice(bytes)
+            .with_context(|| format!("Failed to parse the index of {}", repo.name))
+    }
 
-        // Write to destination
-        let mut file = File::create(dest_path)
-            .with_context(|| format!("Failed to create destination file: {dest_path:?}"))?;
-        file.write_all(&bytes)
-            .with_context(|| "Failed to write plugin data")?;
+    /// Fetch `path` under the base URL of `repo`, or else under its mirrors
+    fn fetch_from(
+        &self,
+        repo: &RemoteRepository,
+        path: &str,
+        progress: &mut dyn FnMut(u64, Option<u64>),
+    ) -> Result<Vec<u8>> {
+        let mut errors = Vec::new();
+        for base in std::iter::once(&repo.base_url).chain(&repo.mirrors) {
+            let url = join_url(base, path);
+            match fetch_url(&url, progress) {
+                Ok(Some(bytes)) => return Ok(bytes),
+                Ok(None) => errors.push(format!("{url}: not found")),
+                Err(e) => {
+                    log::warn!("{e:#}");
+                    errors.push(format!("{e:#}"));
+                }
+            }
+        }
+        bail!("{}", errors.join("; "))
+    }
 
-        Ok(plugin_info)
+    /// Whether this shell can run `entry`
+    fn is_compatible(&self, entry: &IndexEntry, nexus: &Version) -> bool {
+        let min_ok = Version::parse(&entry.min_nexus_version).is_ok_and(|min| *nexus >= min);
+        let max_ok = entry.max_nexus_version.as_deref().map_or(true, |max| {
+            Version::parse(max).is_ok_and(|max| *nexus <= max)
+        });
+        min_ok
+            && max_ok
+            && self
+                .is_platform_compatible(&entry.platforms)
+                .unwrap_or(false)
     }
 
     /// Check if plugin is compatible with current platform
@@ -164,6 +448,16 @@ impl RemotePluginManager {
            platforms.iter().any(|p| p == "all" || p == &current_platform))
     }
 
+    /// Check a downloaded artifact against its index entry
+    fn check_artifact(&self, entry: &IndexEntry, data: &[u8]) -> Result<()> {
+        if let Some(size) = entry.size {
+            if data.len() as u64 != size {
+                bail!("Size mismatch: expected {} bytes, got {}", size, data.len());
+            }
+        }
+        self.verify_checksum(data, &entry.sha256)
+    }
+
     /// Verify plugin checksum
     fn verify_checksum(&self, data: &[u8], expected_checksum: &str) -> Result<()> {
         use sha2::{Digest, Sha256};
@@ -173,7 +467,7 @@ impl RemotePluginManager {
         let computed_hash = hasher.finalize();
         let computed_hex = hex::encode(computed_hash);
 
-        if computed_hex == expected_checksum {
+        if computed_hex.eq_ignore_ascii_case(expected_checksum) {
             Ok(())
         } else {
             anyhow::bail!(
@@ -184,7 +478,7 @@ impl RemotePluginManager {
         }
     }
 
-    /// Verify plugin signature
+    /// Verify a signature of `data`
     fn verify_signature(&self, data: &[u8], signature: &str, public_key: &str) -> Result<()> {
         use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 
@@ -219,8 +513,9 @@ impl RemotePluginManager {
             .map_err(|_| anyhow::anyhow!("Signature verification failed"))
     }
 
-    /// List available plugins from all repositories
+    /// The newest compatible release of each plugin, by repository
     pub fn list_available_plugins(&self) -> Result<HashMap<String, Vec<RemotePluginInfo>>> {
+        let nexus = Version::parse(NEXUS_VERSION)?;
         let mut result = HashMap::new();
 
         for repo in &self.repositories {
@@ -228,12 +523,34 @@ impl RemotePluginManager {
                 continue;
             }
 
-            match self.fetch_repository_catalog(repo) {
-                Ok(plugins) => {
+            match self.index(repo) {
+                Ok(index) => {
+                    let plugins = index
+                        .plugins
+                        .into_iter()
+                        .filter_map(|(name, entries)| {
+                            let entry = entries
+                                .into_iter()
+                                .filter(|entry| self.is_compatible(entry, &nexus))
+                                .filter_map(|entry| {
+                                    Version::parse(&entry.version)
+                                        .ok()
+                                        .map(|version| (version, entry))
+                                })
+                                .max_by(|(a, _), (b, _)| a.cmp(b))?
+                                .1;
+                            let resolved = ResolvedPlugin {
+                                name,
+                                repository: repo.clone(),
+                                entry,
+                            };
+                            Some(resolved.info())
+                        })
+                        .collect();
                     result.insert(repo.name.clone(), plugins);
                 }
                 Err(e) => {
-                    log::warn!("Failed to fetch catalog from {}: {}", repo.name, e);
+                    log::warn!("Failed to fetch catalog from {}: {:#}", repo.name, e);
                 }
             }
         }
@@ -241,71 +558,35 @@ impl RemotePluginManager {
         Ok(result)
     }
 
-    /// Fetch catalog from repository
-    fn fetch_repository_catalog(&self, repo: &RemoteRepository) -> Result<Vec<RemotePluginInfo>> {
-        let catalog_url = format!("{}/api/v1/plugins/catalog", repo.base_url);
-        let response = ureq::get(&catalog_url)
-            .set("User-Agent", &self.user_agent)
-            .call()
-            .with_context(|| format!("Failed to fetch catalog from {catalog_url}"))?;
-
-        let body = response
-            .into_string()
-            .with_context(|| "Failed to read catalog response")?;
-        let plugins: Vec<RemotePluginInfo> =
-            serde_json::from_str(&body).with_context(|| "Failed to parse plugin catalog")?;
-
-        Ok(plugins)
-    }
-
     /// Search for plugins by name or description
     pub fn search_plugins(&self, query: &str) -> Result<Vec<RemotePluginInfo>> {
-        let mut results = Vec::new();
         let query_lower = query.to_lowercase();
-
-        for repo in &self.repositories {
-            if !repo.enabled {
-                continue;
-            }
-
-            match self.fetch_repository_catalog(repo) {
-                Ok(plugins) => {
-                    for plugin in plugins {
-                        if plugin.name.to_lowercase().contains(&query_lower)
-                            || plugin.description.to_lowercase().contains(&query_lower)
-                        {
-                            results.push(plugin);
-                        }
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Failed to search in repository {}: {}", repo.name, e);
-                }
-            }
-        }
-
+        let mut results: Vec<RemotePluginInfo> = self
+            .list_available_plugins()?
+            .into_values()
+            .flatten()
+            .filter(|plugin| {
+                plugin.name.to_lowercase().contains(&query_lower)
+                    || plugin.description.to_lowercase().contains(&query_lower)
+            })
+            .collect();
+        results.sort_by(|a, b| a.id.cmp(&b.id));
         Ok(results)
     }
 
-    /// Update repository metadata cache
+    /// Refresh the cached index of every enabled repository
     pub fn update_cache(&self) -> Result<()> {
+        if self.offline {
+            bail!("Cannot update the plugin cache while offline");
+        }
         for repo in &self.repositories {
             if !repo.enabled {
                 continue;
             }
 
-            let cache_file = self.cache_dir.join(format!("{}-catalog.json", repo.name));
-
-            match self.fetch_repository_catalog(repo) {
-                Ok(plugins) => {
-                    let json = serde_json::to_string_pretty(&plugins)?;
-                    std::fs::write(&cache_file, json)
-                        .with_context(|| format!("Failed to write cache file: {cache_file:?}"))?;
-                    log::info!("Updated cache for repository: {}", repo.name);
-                }
-                Err(e) => {
-                    log::error!("Failed to update cache for {}: {}", repo.name, e);
-                }
+            match self.index(repo) {
+                Ok(_) => log::info!("Updated cache for repository: {}", repo.name),
+                Err(e) => log::error!("Failed to update cache for {}: {:#}", repo.name, e),
             }
         }
         Ok(())
@@ -330,6 +611,7 @@ impl Default for RemotePluginManager {
             public_key: official_key,
             priority: 100,
             enabled: true,
+            mirrors: Vec::new(),
         });
 
         // Add community repository (key loaded via env/file/built-in)
@@ -340,12 +622,86 @@ impl Default for RemotePluginManager {
             public_key: community_key,
             priority: 50,
             enabled: true,
+            mirrors: Vec::new(),
         });
 
         manager
     }
 }
 
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// `path` under `base`, or `path` itself when it is a URL
+fn join_url(base: &str, path: &str) -> String {
+    if is_url(path) {
+        path.to_string()
+    } else {
+        format!(
+            "{}/{}",
+            base.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+}
+
+/// The body at `url`, or `None` when there is nothing there, reporting the
+/// bytes read so far and the total, when known, to `progress`
+pub fn fetch_url(url: &str, progress: &mut dyn FnMut(u64, Option<u64>)) -> Result<Option<Vec<u8>>> {
+    if url.starts_with("https://") {
+        return fetch_with_curl(url, progress);
+    }
+    match ureq::get(url).set("User-Agent", USER_AGENT).call() {
+        Ok(response) => {
+            let total = response
+                .header("Content-Length")
+                .and_then(|length| length.parse().ok());
+            let mut reader = response.into_reader();
+            let mut bytes = Vec::new();
+            let mut chunk = [0u8; 64 * 1024];
+            loop {
+                let read = reader
+                    .read(&mut chunk)
+                    .with_context(|| format!("Failed to download {url}"))?;
+                if read == 0 {
+                    break;
+                }
+                bytes.extend_from_slice(&chunk[..read]);
+                progress(bytes.len() as u64, total);
+            }
+            Ok(Some(bytes))
+        }
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(anyhow!("Failed to download {url}: {e}")),
+    }
+}
+
+/// Fetch an `https` URL with the system's curl
+fn fetch_with_curl(
+    url: &str,
+    progress: &mut dyn FnMut(u64, Option<u64>),
+) -> Result<Option<Vec<u8>>> {
+    let output = Command::new("curl")
+        .args(["-sSL", "--fail", "--proto", "=https", "--proto-redir", "=https"])
+        .args(["-A", USER_AGENT, "--", url])
+        .output()
+        .map_err(|_| {
+            anyhow!("Failed to download {url}: the shell has no TLS and there is no system curl to hand it to")
+        })?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(0) => {
+            let length = output.stdout.len() as u64;
+            progress(length, Some(length));
+            Ok(Some(output.stdout))
+        }
+        // --fail reports HTTP errors with status 22
+        Some(22) if stderr.contains("404") => Ok(None),
+        _ => bail!("Failed to download {url}: {}", stderr.trim()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -371,6 +727,7 @@ mod tests {
             public_key: "test-key".to_string(),
             priority: 75,
             enabled: true,
+            mirrors: Vec::new(),
         };
 
         manager.add_repository(repo.clone());
@@ -396,4 +753,38 @@ mod tests {
             .is_platform_compatible(&["nonexistent-platform".to_string()])
             .unwrap());
     }
+
+    #[test]
+    fn test_nexus_version_bounds() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = RemotePluginManager::new(temp_dir.path()).unwrap();
+        let nexus = Version::parse("1.4.0").unwrap();
+        let entry = |min: &str, max: Option<&str>| IndexEntry {
+            version: "1.0.0".to_string(),
+            url: "p.wasm".to_string(),
+            sha256: String::new(),
+            size: None,
+            min_nexus_version: min.to_string(),
+            max_nexus_version: max.map(str::to_string),
+            platforms: Vec::new(),
+            description: String::new(),
+            author: String::new(),
+        };
+
+        assert!(manager.is_compatible(&entry("1.4.0", None), &nexus));
+        assert!(manager.is_compatible(&entry("0.1.0", Some("1.4.0")), &nexus));
+        assert!(!manager.is_compatible(&entry("1.5.0", None), &nexus));
+        assert!(!manager.is_compatible(&entry("0.1.0", Some("1.3.9")), &nexus));
+        assert!(!manager.is_compatible(&entry("not a version", None), &nexus));
+    }
+
+    #[test]
+    fn test_join_url() {
+        assert_eq!(join_url("http://r/", "/index.json"), "http://r/index.json");
+        assert_eq!(join_url("http://r", "a/p.wasm"), "http://r/a/p.wasm");
+        assert_eq!(
+            join_url("http://r", "https://cdn/p.wasm"),
+            "https://cdn/p.wasm"
+        );
+    }
 }
@@ -2,28 +2,75 @@
 //!
 //! This module provides a comprehensive WASI-like runtime for WebAssembly plugins
 //! using Pure Rust components without wasmtime dependencies.
+//!
+//! A plugin is a `wasm32-wasip1` module. Each command it exports runs in a
+//! fresh instance with its own [`WasiCtx`], built from the capabilities
+//! granted to the plugin:
+//!
+//! - `file_read` and `file_write` preopen the working directory as `.`,
+//!   read-only or writable; `file_read:PATH` and `file_write:PATH` preopen
+//!   PATH under the name it is given by
+//! - `env_read` exposes the shell's environment, which is empty otherwise
+//! - `network_request` lets the plugin use sockets
+//!
+//! The module's `_start` runs as a command named after the plugin, and each
+//! command the plugin lists under `commands` in its manifest runs the exported
+//! function of its name. The manifest is JSON in a custom section named
+//! [`MANIFEST_SECTION`]. A command is listed by name, or as a
+//! [`CommandSpec`] object giving its export, help and completion:
+//!
+//! ```json
+//! {"name": "greeter", "capabilities": ["env_read"],
+//!  "commands": ["hello", {"name": "greet", "synopsis": "Greet someone",
+//!                         "usage": "greet [--loud] NAME", "flags": [["--loud", "Shout"]]}]}
+//! ```
+//!
+//! A command spec can also list shell events under `hooks`, such as
+//! `"hooks": ["cwd_changed"]`; the shell runs the command when each fires,
+//! with the event's details as arguments.
+//!
+//! Plugins can complete the arguments of commands and add segments to the
+//! prompt, listing a [`CompleterSpec`] per command under `completers` and a
+//! [`SegmentSpec`] per segment under `segments`:
+//!
+//! ```json
+//! {"name": "kube", "capabilities": ["env_read", "file_read:.kube"],
+//!  "completers": [{"command": "kubectl", "export": "complete_kubectl"}],
+//!  "segments": [{"name": "kube", "export": "kube_context", "timeout_ms": 100}]}
+//! ```
+//!
+//! A completer's export runs with the line being edited and the byte offset
+//! of the cursor in it as arguments, and prints a candidate for the word
+//! under the cursor per line. A segment's export runs without arguments and
+//! prints the segment's text; prompt templates show it as `{plugin:kube}`.
+//! Both run like commands, with the capabilities granted to the plugin.
 
 use anyhow::{anyhow, Context, Result};
 use log::{debug, info};
+use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
-    io::{self, Write},
     path::{Path, PathBuf},
     sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::SystemTime,
 };
 use tokio::sync::{Mutex, RwLock, Semaphore};
 #[cfg(feature = "wasi-runtime")]
-use wasmi::{Caller, Engine, Instance, Linker, Module, Store, Val};
+use wasmi::{Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Val};
 
 use crate::{
     component::{ComponentRegistry, ComponentValue},
-    permissions::PluginPermissions,
-    registrar::PluginRegistrar,
     security::SecurityContext,
+    wasi::{self, WasiCtx},
 };
 
+/// Name of the custom section holding a plugin's manifest
+pub const MANIFEST_SECTION: &str = "nxsh_plugin";
+
+/// Exit status of a command whose plugin trapped, as wasmtime reports it
+const TRAPPED: i32 = 134;
+
 /// Runtime configuration
 #[derive(Debug, Clone)]
 pub struct RuntimeConfig {
@@ -55,7 +102,6 @@ impl Default for RuntimeConfig {
 #[allow(dead_code)]
 pub struct WasiPluginRuntime {
     engine: Engine,
-    linker: Arc<RwLock<Linker<RuntimeContext>>>,
     plugins: Arc<RwLock<HashMap<String, LoadedPlugin>>>,
     capability_manager: CapabilityManager,
     component_registry: ComponentRegistry,
@@ -63,79 +109,62 @@ pub struct WasiPluginRuntime {
     execution_semaphore: Arc<Semaphore>,
 }
 
+/// A plugin compiled and linked for loading, with the commands it runs
+type Prepared = (
+    Module,
+    Linker<RuntimeContext>,
+    BTreeMap<String, CommandSpec>,
+);
+
 /// Runtime context for plugin execution
 #[derive(Debug)]
 pub struct RuntimeContext {
-    pub security_context: SecurityContext,
-    pub permissions: PluginPermissions,
-    pub registrar: PluginRegistrar,
-    pub file_descriptors: HashMap<i32, FileDescriptor>,
-    pub environment: HashMap<String, String>,
-    pub args: Vec<String>,
+    pub wasi: WasiCtx,
+    limits: StoreLimits,
     pub start_time: SystemTime,
 }
 
-impl Default for RuntimeContext {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl RuntimeContext {
-    pub fn new() -> Self {
-        let mut file_descriptors = HashMap::new();
-
-        // Add standard file descriptors
-        file_descriptors.insert(0, FileDescriptor::stdin());
-        file_descriptors.insert(1, FileDescriptor::stdout());
-        file_descriptors.insert(2, FileDescriptor::stderr());
-
+    pub fn new(wasi: WasiCtx, max_memory_bytes: u64) -> Self {
         Self {
-            security_context: SecurityContext::new_restricted(),
-            permissions: PluginPermissions::default(),
-            registrar: PluginRegistrar::new(),
-            file_descriptors,
-            environment: std::env::vars().collect(),
-            args: Vec::new(),
+            wasi,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(usize::try_from(max_memory_bytes).unwrap_or(usize::MAX))
+                .build(),
             start_time: SystemTime::now(),
         }
     }
 }
 
-/// File descriptor for WASI emulation
-#[derive(Debug, Clone)]
-pub enum FileDescriptor {
-    Stdin,
-    Stdout,
-    Stderr,
-    File {
-        path: PathBuf,
-        readable: bool,
-        writable: bool,
-    },
-}
-
-impl FileDescriptor {
-    pub fn stdin() -> Self {
-        Self::Stdin
-    }
-
-    pub fn stdout() -> Self {
-        Self::Stdout
-    }
-
-    pub fn stderr() -> Self {
-        Self::Stderr
-    }
-}
-
-/// Loaded plugin information  
+/// Loaded plugin information
 #[derive(Debug)]
 pub struct LoadedPlugin {
     pub id: String,
     pub module: Arc<Module>,
     pub metadata: PluginMetadata,
     pub load_time: SystemTime,
+    /// Host functions for this module, with stubs for what it imports beyond them
+    linker: Arc<Linker<RuntimeContext>>,
+    /// Commands by name, each with the export it runs
+    commands: BTreeMap<String, CommandSpec>,
+}
+
+impl LoadedPlugin {
+    /// The plugin's completer for the arguments of `command`
+    fn completer(&self, command: &str) -> Option<&CompleterSpec> {
+        self.metadata
+            .completers
+            .iter()
+            .find(|completer| completer.command == command)
+    }
+
+    /// The plugin's prompt segment named `name`
+    fn segment(&self, name: &str) -> Option<&SegmentSpec> {
+        self.metadata
+            .segments
+            .iter()
+            .find(|segment| segment.name == name)
+    }
 }
 
 /// Plugin metadata
@@ -145,6 +174,12 @@ pub struct PluginMetadata {
     pub version: String,
     pub description: String,
     pub permissions: Vec<String>,
+    /// Commands to offer besides `_start`
+    pub commands: Vec<CommandSpec>,
+    /// Exports completing the arguments of commands
+    pub completers: Vec<CompleterSpec>,
+    /// Exports rendering prompt segments
+    pub segments: Vec<SegmentSpec>,
 }
 
 impl Default for PluginMetadata {
@@ -154,8 +189,187 @@ impl Default for PluginMetadata {
             version: "0.0.0".to_string(),
             description: "No description".to_string(),
             permissions: Vec::new(),
+            commands: Vec::new(),
+            completers: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// A plugin's manifest, as found in its [`MANIFEST_SECTION`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub capabilities: Vec<String>,
+    pub commands: Vec<ManifestCommand>,
+    pub completers: Vec<CompleterSpec>,
+    pub segments: Vec<SegmentSpec>,
+}
+
+/// A command in a manifest, by name alone or with its help and completion
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ManifestCommand {
+    Name(String),
+    Spec(CommandSpec),
+}
+
+impl ManifestCommand {
+    /// The command's spec; a bare name has no help and runs the export of
+    /// that name
+    pub fn into_spec(self) -> CommandSpec {
+        match self {
+            ManifestCommand::Name(name) => CommandSpec {
+                name,
+                ..CommandSpec::default()
+            },
+            ManifestCommand::Spec(spec) => spec,
+        }
+    }
+
+    /// Name the command is run by
+    pub fn name(&self) -> &str {
+        match self {
+            ManifestCommand::Name(name) => name,
+            ManifestCommand::Spec(spec) => &spec.name,
+        }
+    }
+}
+
+/// A plugin command: the export it runs, its help and how its arguments complete
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CommandSpec {
+    /// Name the command is run by
+    pub name: String,
+    /// Exported function to run: `name` if not given, or `_start` for the
+    /// command named after the plugin
+    pub export: Option<String>,
+    /// One-line description
+    pub synopsis: String,
+    /// Longer help text
+    pub help: String,
+    /// Usage line, whose placeholders tell completion what arguments are
+    pub usage: String,
+    /// Flags and their descriptions, as `[flag, description]` pairs
+    pub flags: Vec<(String, String)>,
+    /// Shell events to run the command on, such as `cwd_changed`
+    pub hooks: Vec<String>,
+}
+
+/// A completer: the export completing the arguments of a command
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CompleterSpec {
+    /// Command whose arguments it completes; not necessarily the plugin's
+    pub command: String,
+    /// Exported function run with the line and the cursor offset
+    pub export: String,
+}
+
+/// A prompt segment: the export printing its text
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SegmentSpec {
+    /// Name prompt templates show it by, as `{plugin:NAME}`
+    pub name: String,
+    /// Exported function to run: `name` if not given
+    pub export: Option<String>,
+    /// How long the prompt waits for it, by default as long as for the
+    /// segments that run git
+    pub timeout_ms: Option<u64>,
+}
+
+impl SegmentSpec {
+    /// Exported function the segment runs
+    pub fn export(&self) -> &str {
+        self.export.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// The manifest embedded in `wasm`, if it has one
+pub fn read_manifest(wasm: &[u8]) -> Result<Option<Manifest>> {
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let wasmparser::Payload::CustomSection(section) =
+            payload.context("Invalid WebAssembly module")?
+        {
+            if section.name() == MANIFEST_SECTION {
+                let manifest =
+                    serde_json::from_slice(section.data()).context("Invalid plugin manifest")?;
+                return Ok(Some(manifest));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// What a plugin command wrote and how it ended
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Check that `capability` is one WASI plugins can be granted
+pub(crate) fn check_capability(capability: &str) -> Result<()> {
+    let (name, path) = match capability.split_once(':') {
+        Some((name, path)) => (name, Some(path)),
+        None => (capability, None),
+    };
+    match (name, path) {
+        ("file_read" | "file_write", Some(path)) if !path.is_empty() => Ok(()),
+        ("file_read" | "file_write" | "env_read" | "network_request", None) => Ok(()),
+        _ => Err(anyhow!(
+            "Capability '{}' is not available to WASI plugins",
+            capability
+        )),
+    }
+}
+
+/// The WASI context for one run under `grants`
+fn wasi_context(
+    grants: &[String],
+    args: Vec<String>,
+    env: &HashMap<String, String>,
+    cwd: &Path,
+) -> Result<WasiCtx> {
+    let mut ctx = WasiCtx::new(args);
+    // Guest name to host directory and whether it is writable
+    let mut preopens: BTreeMap<String, (PathBuf, bool)> = BTreeMap::new();
+    for grant in grants {
+        let (name, path) = match grant.split_once(':') {
+            Some((name, path)) => (name, Some(path)),
+            None => (grant.as_str(), None),
+        };
+        match name {
+            "file_read" | "file_write" => {
+                let (guest, host) = match path {
+                    Some(path) => (path.to_string(), cwd.join(path)),
+                    None => (".".to_string(), cwd.to_path_buf()),
+                };
+                let entry = preopens.entry(guest).or_insert((host, false));
+                entry.1 |= name == "file_write";
+            }
+            "env_read" => {
+                let mut vars: Vec<_> = env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                vars.sort();
+                ctx.set_env(vars);
+            }
+            "network_request" => ctx.allow_network(true),
+            _ => {}
         }
     }
+    for (guest, (host, writable)) in preopens {
+        ctx.preopen(&guest, &host, writable)
+            .with_context(|| format!("Cannot preopen {}", host.display()))?;
+    }
+    Ok(ctx)
 }
 
 impl WasiPluginRuntime {
@@ -168,7 +382,6 @@ impl WasiPluginRuntime {
     /// Create a new WASI plugin runtime with custom configuration
     pub fn with_config(config: RuntimeConfig) -> Result<Self> {
         let engine = Engine::default();
-        let linker = Linker::new(&engine);
 
         // Initialize capability manager
         let capability_manager = CapabilityManager::new(SecurityContext::new_restricted())?;
@@ -181,7 +394,6 @@ impl WasiPluginRuntime {
 
         let runtime = Self {
             engine,
-            linker: Arc::new(RwLock::new(linker)),
             plugins: Arc::new(RwLock::new(HashMap::new())),
             capability_manager,
             component_registry,
@@ -192,7 +404,7 @@ impl WasiPluginRuntime {
         Ok(runtime)
     }
 
-    /// Initialize the runtime with host functions and capabilities
+    /// Initialize the runtime and its capabilities
     pub async fn initialize(&mut self) -> Result<()> {
         info!("Initializing WASI plugin runtime");
 
@@ -202,233 +414,86 @@ impl WasiPluginRuntime {
             .await
             .context("Failed to initialize capability manager")?;
 
-        // Setup host functions
-        self.setup_host_functions()
-            .await
-            .context("Failed to setup host functions")?;
-
         info!("WASI plugin runtime initialized successfully");
         Ok(())
     }
 
-    /// Setup WASI host functions
-    async fn setup_host_functions(&mut self) -> Result<()> {
-        let mut linker = self.linker.write().await;
-
-        // WASI core functions
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "proc_exit",
-            |_: Caller<'_, RuntimeContext>, exit_code: i32| {
-                debug!("proc_exit called with code: {exit_code}");
-                // In a real implementation, this would exit the plugin
-            },
-        )?;
-
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "fd_write",
-            |mut caller: Caller<'_, RuntimeContext>,
-             fd: i32,
-             iovs: i32,
-             iovs_len: i32,
-             nwritten: i32|
-             -> Result<i32, wasmi::Error> {
-                let memory = match caller.get_export("memory") {
-                    Some(wasmi::Extern::Memory(mem)) => mem,
-                    _ => return Ok(8), // EBADF
-                };
-
-                let mut total_written = 0u32;
-
-                for i in 0..iovs_len {
-                    let iov_base = iovs + i * 8;
-
-                    // Read iovec structure
-                    let mut buf = [0u8; 8];
-                    memory
-                        .read(&caller, iov_base as usize, &mut buf)
-                        .map_err(|_e| wasmi::Error::new("Memory read failed"))?;
-
-                    let ptr = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
-                    let len = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
-
-                    // Read data
-                    let mut data = vec![0u8; len as usize];
-                    memory
-                        .read(&caller, ptr as usize, &mut data)
-                        .map_err(|_e| wasmi::Error::new("Memory read failed"))?;
-
-                    // Write to appropriate file descriptor
-                    match fd {
-                        1 => {
-                            // stdout
-                            print!("{}", String::from_utf8_lossy(&data));
-                            io::stdout().flush().ok();
-                            total_written += len;
-                        }
-                        2 => {
-                            // stderr
-                            eprint!("{}", String::from_utf8_lossy(&data));
-                            io::stderr().flush().ok();
-                            total_written += len;
-                        }
-                        _ => {
-                            // Other file descriptors (simplified)
-                            total_written += len;
-                        }
-                    }
-                }
-
-                // Write total bytes written
-                let written_bytes = total_written.to_le_bytes();
-                memory
-                    .write(&mut caller, nwritten as usize, &written_bytes)
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-
-                Ok(0) // Success
-            },
-        )?;
-
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "environ_sizes_get",
-            |mut caller: Caller<'_, RuntimeContext>,
-             environc: i32,
-             environ_buf_size: i32|
-             -> Result<i32, wasmi::Error> {
-                let memory = match caller.get_export("memory") {
-                    Some(wasmi::Extern::Memory(mem)) => mem,
-                    _ => return Ok(8), // EBADF
-                };
-
-                let context = caller.data();
-                let env_count = context.environment.len() as u32;
-                let mut buf_size = 0u32;
-
-                for (key, value) in &context.environment {
-                    buf_size += (key.len() + value.len() + 2) as u32; // key=value\0
-                }
-
-                // Write environment count
-                memory
-                    .write(&mut caller, environc as usize, &env_count.to_le_bytes())
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-
-                // Write buffer size
-                memory
-                    .write(
-                        &mut caller,
-                        environ_buf_size as usize,
-                        &buf_size.to_le_bytes(),
-                    )
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-
-                Ok(0) // Success
-            },
-        )?;
-
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "environ_get",
-            |mut caller: Caller<'_, RuntimeContext>,
-             environ: i32,
-             environ_buf: i32|
-             -> Result<i32, wasmi::Error> {
-                let memory = match caller.get_export("memory") {
-                    Some(wasmi::Extern::Memory(mem)) => mem,
-                    _ => return Ok(8), // EBADF
-                };
-
-                let environment = caller.data().environment.clone();
-                let mut buf_offset = environ_buf as usize;
-                let mut ptr_offset = environ as usize;
-
-                for (key, value) in &environment {
-                    let env_string = format!("{key}={value}\0");
-                    let env_bytes = env_string.as_bytes();
-
-                    // Write pointer to string
-                    memory
-                        .write(&mut caller, ptr_offset, &(buf_offset as u32).to_le_bytes())
-                        .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-                    ptr_offset += 4;
-
-                    // Write string
-                    memory
-                        .write(&mut caller, buf_offset, env_bytes)
-                        .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-                    buf_offset += env_bytes.len();
-                }
-
-                Ok(0) // Success
-            },
-        )?;
-
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "args_sizes_get",
-            |mut caller: Caller<'_, RuntimeContext>,
-             argc: i32,
-             argv_buf_size: i32|
-             -> Result<i32, wasmi::Error> {
-                let memory = match caller.get_export("memory") {
-                    Some(wasmi::Extern::Memory(mem)) => mem,
-                    _ => return Ok(8), // EBADF
-                };
-
-                let args = caller.data().args.clone();
-                let arg_count = args.len() as u32;
-                let mut buf_size = 0u32;
-
-                for arg in &args {
-                    buf_size += (arg.len() + 1) as u32; // arg\0
-                }
-
-                // Write argument count
-                memory
-                    .write(&mut caller, argc as usize, &arg_count.to_le_bytes())
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-
-                // Write buffer size
-                memory
-                    .write(&mut caller, argv_buf_size as usize, &buf_size.to_le_bytes())
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
-
-                Ok(0) // Success
-            },
-        )?;
-
-        linker.func_wrap(
-            "wasi_snapshot_preview1",
-            "clock_time_get",
-            |mut caller: Caller<'_, RuntimeContext>,
-             _id: i32,
-             _precision: i64,
-             time: i32|
-             -> Result<i32, wasmi::Error> {
-                let memory = match caller.get_export("memory") {
-                    Some(wasmi::Extern::Memory(mem)) => mem,
-                    _ => return Ok(8), // EBADF
-                };
-
-                let now = SystemTime::now();
-                let timestamp = now
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_nanos() as u64;
-
-                // Write timestamp
-                memory
-                    .write(&mut caller, time as usize, &timestamp.to_le_bytes())
-                    .map_err(|_e| wasmi::Error::new("Memory write failed"))?;
+    /// Check that the plugin `wasm_bytes` with `metadata` compiles, links
+    /// and exports its commands, without loading it
+    pub fn validate_plugin(
+        &self,
+        plugin_id: &str,
+        wasm_bytes: &[u8],
+        metadata: &PluginMetadata,
+    ) -> Result<()> {
+        self.prepare(plugin_id, wasm_bytes, metadata).map(|_| ())
+    }
 
-                Ok(0) // Success
-            },
-        )?;
+    /// Compile and link `wasm_bytes`, finding the export each command runs
+    fn prepare(
+        &self,
+        plugin_id: &str,
+        wasm_bytes: &[u8],
+        metadata: &PluginMetadata,
+    ) -> Result<Prepared> {
+        let module = Module::new(&self.engine, wasm_bytes)
+            .context("Failed to compile WebAssembly module")?;
 
-        debug!("WASI host functions setup completed");
-        Ok(())
+        let mut linker = Linker::new(&self.engine);
+        wasi::add_to_linker(&mut linker, |context: &mut RuntimeContext| {
+            &mut context.wasi
+        })?;
+        wasi::link_stubs(&mut linker, &module)
+            .with_context(|| format!("Plugin '{plugin_id}' cannot be linked"))?;
+
+        let mut commands = BTreeMap::new();
+        if module.get_export("_start").is_some() {
+            let spec = CommandSpec {
+                name: metadata.name.clone(),
+                export: Some("_start".to_string()),
+                synopsis: metadata.description.clone(),
+                ..CommandSpec::default()
+            };
+            commands.insert(metadata.name.clone(), spec);
+        }
+        for spec in &metadata.commands {
+            // A spec for the command named after the plugin describes `_start`
+            let export = match &spec.export {
+                Some(export) => export.clone(),
+                None if commands.contains_key(&spec.name) => "_start".to_string(),
+                None => spec.name.clone(),
+            };
+            if !matches!(module.get_export(&export), Some(wasmi::ExternType::Func(_))) {
+                return Err(anyhow!(
+                    "Plugin '{}' exports no function '{}'",
+                    plugin_id,
+                    export
+                ));
+            }
+            let spec = CommandSpec {
+                export: Some(export),
+                ..spec.clone()
+            };
+            commands.insert(spec.name.clone(), spec);
+        }
+        if commands.is_empty() {
+            return Err(anyhow!("Plugin '{}' exports no commands", plugin_id));
+        }
+        let exports = metadata
+            .completers
+            .iter()
+            .map(|completer| completer.export.as_str())
+            .chain(metadata.segments.iter().map(SegmentSpec::export));
+        for export in exports {
+            if !matches!(module.get_export(export), Some(wasmi::ExternType::Func(_))) {
+                return Err(anyhow!(
+                    "Plugin '{}' exports no function '{}'",
+                    plugin_id,
+                    export
+                ));
+            }
+        }
+        Ok((module, linker, commands))
     }
 
     /// Load a plugin from WebAssembly bytes
@@ -438,17 +503,52 @@ impl WasiPluginRuntime {
         wasm_bytes: &[u8],
         metadata: PluginMetadata,
     ) -> Result<()> {
-        let module = Module::new(&self.engine, wasm_bytes)
-            .context("Failed to compile WebAssembly module")?;
+        let (module, linker, commands) = self.prepare(&plugin_id, wasm_bytes, &metadata)?;
 
+        let mut plugins = self.plugins.write().await;
+        for (command, other) in plugins
+            .values()
+            .flat_map(|other| other.commands.keys().map(move |command| (command, other)))
+        {
+            if commands.contains_key(command) {
+                return Err(anyhow!(
+                    "Command '{}' of plugin '{}' is already provided by '{}'",
+                    command,
+                    plugin_id,
+                    other.id
+                ));
+            }
+        }
+        for other in plugins.values() {
+            for completer in &metadata.completers {
+                if other.completer(&completer.command).is_some() {
+                    return Err(anyhow!(
+                        "Completion for '{}' of plugin '{}' is already provided by '{}'",
+                        completer.command,
+                        plugin_id,
+                        other.id
+                    ));
+                }
+            }
+            for segment in &metadata.segments {
+                if other.segment(&segment.name).is_some() {
+                    return Err(anyhow!(
+                        "Prompt segment '{}' of plugin '{}' is already provided by '{}'",
+                        segment.name,
+                        plugin_id,
+                        other.id
+                    ));
+                }
+            }
+        }
         let plugin = LoadedPlugin {
             id: plugin_id.clone(),
             module: Arc::new(module),
             metadata,
             load_time: SystemTime::now(),
+            linker: Arc::new(linker),
+            commands,
         };
-
-        let mut plugins = self.plugins.write().await;
         plugins.insert(plugin_id.clone(), plugin);
 
         info!("Plugin '{plugin_id}' loaded successfully");
@@ -470,6 +570,253 @@ impl WasiPluginRuntime {
             .await
     }
 
+    /// Grant a capability to a loaded plugin
+    pub async fn grant_capability(&self, plugin_id: &str, capability: &str) -> Result<()> {
+        check_capability(capability)?;
+        self.capability_manager
+            .grant_capability(plugin_id, capability)
+            .await
+    }
+
+    /// The commands loaded plugins provide, in order
+    pub async fn commands(&self) -> Vec<String> {
+        let plugins = self.plugins.read().await;
+        let mut commands: Vec<String> = plugins
+            .values()
+            .flat_map(|plugin| plugin.commands.keys().cloned())
+            .collect();
+        commands.sort();
+        commands
+    }
+
+    /// The commands loaded plugins provide with the plugin providing each,
+    /// in name order
+    pub async fn command_specs(&self) -> Vec<(String, CommandSpec)> {
+        let plugins = self.plugins.read().await;
+        let mut specs: Vec<(String, CommandSpec)> = plugins
+            .values()
+            .flat_map(|plugin| {
+                plugin
+                    .commands
+                    .values()
+                    .map(|spec| (plugin.id.clone(), spec.clone()))
+            })
+            .collect();
+        specs.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        specs
+    }
+
+    /// The completers of loaded plugins with the plugin providing each, in
+    /// command order
+    pub async fn completers(&self) -> Vec<(String, CompleterSpec)> {
+        let plugins = self.plugins.read().await;
+        let mut completers: Vec<(String, CompleterSpec)> = plugins
+            .values()
+            .flat_map(|plugin| {
+                plugin
+                    .metadata
+                    .completers
+                    .iter()
+                    .map(|spec| (plugin.id.clone(), spec.clone()))
+            })
+            .collect();
+        completers.sort_by(|a, b| a.1.command.cmp(&b.1.command));
+        completers
+    }
+
+    /// The prompt segments of loaded plugins with the plugin providing each,
+    /// in name order
+    pub async fn segments(&self) -> Vec<(String, SegmentSpec)> {
+        let plugins = self.plugins.read().await;
+        let mut segments: Vec<(String, SegmentSpec)> = plugins
+            .values()
+            .flat_map(|plugin| {
+                plugin
+                    .metadata
+                    .segments
+                    .iter()
+                    .map(|spec| (plugin.id.clone(), spec.clone()))
+            })
+            .collect();
+        segments.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        segments
+    }
+
+    /// Run the plugin command `command` with `args`, giving it `env` and
+    /// `cwd` as far as the plugin's capabilities allow
+    pub async fn run_command(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cwd: &Path,
+    ) -> Result<CommandOutput> {
+        let (plugin_id, export) = {
+            let plugins = self.plugins.read().await;
+            let plugin = plugins
+                .values()
+                .find(|plugin| plugin.commands.contains_key(command))
+                .ok_or_else(|| anyhow!("No plugin provides '{}'", command))?;
+            (
+                plugin.id.clone(),
+                plugin.commands[command]
+                    .export
+                    .clone()
+                    .unwrap_or_else(|| command.to_string()),
+            )
+        };
+        self.run_export(&plugin_id, &export, command, args, env, cwd)
+            .await
+    }
+
+    /// Candidates for the word under the cursor in `line`, from the
+    /// completer a plugin provides for the arguments of `command`
+    pub async fn complete(
+        &self,
+        command: &str,
+        line: &str,
+        cursor: usize,
+        env: &HashMap<String, String>,
+        cwd: &Path,
+    ) -> Result<Vec<String>> {
+        let (plugin_id, export) = {
+            let plugins = self.plugins.read().await;
+            plugins
+                .values()
+                .find_map(|plugin| {
+                    let completer = plugin.completer(command)?;
+                    Some((plugin.id.clone(), completer.export.clone()))
+                })
+                .ok_or_else(|| anyhow!("No plugin completes '{}'", command))?
+        };
+        let args = [line.to_string(), cursor.to_string()];
+        let output = self
+            .run_export(&plugin_id, &export, command, &args, env, cwd)
+            .await?;
+        if output.status != 0 {
+            return Err(anyhow!(
+                "Completion for '{}' exited with status {}",
+                command,
+                output.status
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|candidate| !candidate.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The text of the prompt segment `name`: the first line its export prints
+    pub async fn render_segment(
+        &self,
+        name: &str,
+        env: &HashMap<String, String>,
+        cwd: &Path,
+    ) -> Result<String> {
+        let (plugin_id, export) = {
+            let plugins = self.plugins.read().await;
+            plugins
+                .values()
+                .find_map(|plugin| {
+                    let segment = plugin.segment(name)?;
+                    Some((plugin.id.clone(), segment.export().to_string()))
+                })
+                .ok_or_else(|| anyhow!("No plugin provides prompt segment '{}'", name))?
+        };
+        let output = self
+            .run_export(&plugin_id, &export, name, &[], env, cwd)
+            .await?;
+        if output.status != 0 {
+            return Err(anyhow!(
+                "Prompt segment '{}' exited with status {}",
+                name,
+                output.status
+            ));
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        Ok(text
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_end()
+            .to_string())
+    }
+
+    /// Run `export` of plugin `plugin_id` with `args`, reporting it as
+    /// `command`
+    async fn run_export(
+        &self,
+        plugin_id: &str,
+        export: &str,
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cwd: &Path,
+    ) -> Result<CommandOutput> {
+        let (module, linker) = {
+            let plugins = self.plugins.read().await;
+            let plugin = plugins
+                .get(plugin_id)
+                .ok_or_else(|| anyhow!("Plugin '{}' not found", plugin_id))?;
+            (Arc::clone(&plugin.module), Arc::clone(&plugin.linker))
+        };
+        let grants = self.capability_manager.granted(plugin_id).await;
+        let argv = std::iter::once(command.to_string())
+            .chain(args.iter().cloned())
+            .collect();
+        let wasi = wasi_context(&grants, argv, env, cwd)?;
+
+        let _permit = self.execution_semaphore.acquire().await?;
+        let mut store = self.store(wasi);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .context("Failed to instantiate plugin")?
+            .start(&mut store)
+            .context("Failed to start plugin instance")?;
+        let func = instance
+            .get_func(&store, export)
+            .ok_or_else(|| anyhow!("Function '{}' not found", export))?;
+        let ty = func.ty(&store);
+        if !ty.params().is_empty() {
+            return Err(anyhow!("Function '{}' cannot be run as a command", export));
+        }
+
+        let mut results = vec![Val::I32(0); ty.results().len()];
+        let outcome = func.call(&mut store, &[], &mut results);
+        let (stdout, mut stderr) = store.data_mut().wasi.take_output();
+        let status = match outcome {
+            Ok(()) => match results.first() {
+                Some(Val::I32(status)) => *status,
+                _ => 0,
+            },
+            Err(error) => match error.i32_exit_status() {
+                Some(status) => status,
+                None => {
+                    stderr.extend_from_slice(
+                        format!("{command}: plugin '{plugin_id}' trapped: {error}\n").as_bytes(),
+                    );
+                    TRAPPED
+                }
+            },
+        };
+        debug!("Plugin export '{export}' run as '{command}' exited with status {status}");
+        Ok(CommandOutput {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// A store for one run, with the configured memory limit
+    fn store(&self, wasi: WasiCtx) -> Store<RuntimeContext> {
+        let context = RuntimeContext::new(wasi, self.config.max_memory_bytes);
+        let mut store = Store::new(&self.engine, context);
+        store.limiter(|context| &mut context.limits);
+        store
+    }
+
     /// Execute a function in a loaded plugin
     pub async fn execute_plugin_function(
         &self,
@@ -485,12 +832,14 @@ impl WasiPluginRuntime {
             .get(plugin_id)
             .ok_or_else(|| anyhow!("Plugin '{}' not found", plugin_id))?;
         let plugin_module = Arc::clone(&plugin.module);
+        let linker = Arc::clone(&plugin.linker);
         drop(plugins); // Release the lock early
 
-        let context = RuntimeContext::new();
-        let mut store = Store::new(&self.engine, context);
+        let grants = self.capability_manager.granted(plugin_id).await;
+        let cwd = std::env::current_dir()?;
+        let wasi = wasi_context(&grants, vec![plugin_id.to_string()], &HashMap::new(), &cwd)?;
+        let mut store = self.store(wasi);
 
-        let linker = self.linker.read().await;
         let pre_instance = linker
             .instantiate(&mut store, &plugin_module)
             .context("Failed to instantiate plugin")?;
@@ -498,8 +847,6 @@ impl WasiPluginRuntime {
             .start(&mut store)
             .context("Failed to start plugin instance")?;
 
-        let _execution_timeout = Duration::from_millis(self.config.execution_timeout_ms);
-
         // Execute synchronously to avoid lifetime issues
         let result = self
             .execute_function(&mut store, &instance, function_name, args)
@@ -572,6 +919,7 @@ impl WasiPluginRuntime {
         let removed = plugins.remove(plugin_id).is_some();
 
         if removed {
+            self.capability_manager.revoke_all(plugin_id).await;
             info!("Plugin '{plugin_id}' unloaded successfully");
         }
 
@@ -635,6 +983,18 @@ impl CapabilityManager {
             .map(|caps| caps.contains(&capability.to_string()))
             .unwrap_or(false)
     }
+
+    /// The capabilities granted to a plugin
+    pub async fn granted(&self, plugin_id: &str) -> Vec<String> {
+        let capabilities = self.granted_capabilities.lock().await;
+        capabilities.get(plugin_id).cloned().unwrap_or_default()
+    }
+
+    /// Withdraw every capability granted to a plugin
+    pub async fn revoke_all(&self, plugin_id: &str) {
+        let mut capabilities = self.granted_capabilities.lock().await;
+        capabilities.remove(plugin_id);
+    }
 }
 
 #[cfg(test)]
@@ -660,4 +1020,28 @@ mod tests {
         let runtime = WasiPluginRuntime::with_config(config).unwrap();
         assert!(runtime.config().execution_timeout_ms > 0);
     }
+
+    #[test]
+    fn test_capability_names() {
+        assert!(check_capability("file_read").is_ok());
+        assert!(check_capability("file_write:/tmp").is_ok());
+        assert!(check_capability("network_request").is_ok());
+        assert!(check_capability("file_read:").is_err());
+        assert!(check_capability("command_execute").is_err());
+    }
+
+    #[test]
+    fn test_capabilities_map_to_wasi_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let grants = vec!["file_read".to_string(), "file_write".to_string()];
+        let env = HashMap::from([("HOME".to_string(), "/home/u".to_string())]);
+        let ctx = wasi_context(&grants, vec!["cmd".to_string()], &env, dir.path()).unwrap();
+        let shown = format!("{ctx:?}");
+        // stdin, stdout, stderr and one merged preopen; no environment
+        assert!(shown.contains("descriptor_count: 4"), "{shown}");
+        assert!(shown.contains("env_count: 0"), "{shown}");
+
+        let grants = vec!["file_read:missing".to_string()];
+        assert!(wasi_context(&grants, Vec::new(), &env, dir.path()).is_err());
+    }
 }
@@ -146,6 +146,19 @@ impl SignatureVerifier {
         plugin_path: P,
         private_key: &Ed25519PrivateKey,
         key_id: String,
+    ) -> Result<PluginSignature> {
+        self.sign_plugin_with_chain(plugin_path, private_key, key_id, Vec::new())
+            .await
+    }
+
+    /// Sign a plugin with an intermediate key, embedding the certificates
+    /// that lead from `key_id` to a root key
+    pub async fn sign_plugin_with_chain<P: AsRef<Path>>(
+        &self,
+        plugin_path: P,
+        private_key: &Ed25519PrivateKey,
+        key_id: String,
+        chain: Vec<KeyCertificate>,
     ) -> Result<PluginSignature> {
         let plugin_path = plugin_path.as_ref();
         debug!("Signing plugin: {plugin_path:?}");
@@ -158,10 +171,11 @@ impl SignatureVerifier {
         // Calculate hash
         let hash = self.calculate_hash(&plugin_data);
 
-        // Create signature payload
+        // Create signature payload, with the timestamp the signature records
+        let timestamp = Utc::now();
         let payload = SignaturePayload {
             hash: hash.clone(),
-            timestamp: Utc::now(),
+            timestamp,
             key_id: key_id.clone(),
             algorithm: "Ed25519".to_string(),
         };
@@ -178,13 +192,14 @@ impl SignatureVerifier {
             signature: BASE64.encode(&signature_bytes),
             key_id,
             algorithm: "Ed25519".to_string(),
-            timestamp: Utc::now(),
-            expires_at: Some(Utc::now() + chrono::Duration::days(365)), // 1 year expiration
+            timestamp,
+            expires_at: Some(timestamp + chrono::Duration::days(365)), // 1 year expiration
             metadata: SignatureMetadata {
                 version: "1.0".to_string(),
                 tool: "NexusShell".to_string(),
                 tool_version: env!("CARGO_PKG_VERSION").to_string(),
             },
+            chain,
         };
 
         // Save signature file
@@ -600,6 +615,108 @@ pub struct PluginSignature {
     pub timestamp: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
     pub metadata: SignatureMetadata,
+    /// Certificates from the signing key up to a root key, when it was
+    /// signed with an intermediate key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<KeyCertificate>,
+}
+
+impl PluginSignature {
+    /// Check this detached signature of `plugin_data` against a base64
+    /// Ed25519 public key, without the trusted key store, revocation log or
+    /// TUF metadata of [`SignatureVerifier`]
+    pub fn check(&self, plugin_data: &[u8], public_key: &str) -> VerificationResult {
+        let hash = format!("sha256:{}", hex::encode(Sha256::digest(plugin_data)));
+        if hash != self.hash {
+            return VerificationResult::failed("Plugin hash does not match signature".to_string());
+        }
+        if self
+            .expires_at
+            .is_some_and(|expires_at| Utc::now() > expires_at)
+        {
+            return VerificationResult::failed("Plugin signature has expired".to_string());
+        }
+        let payload = SignaturePayload {
+            hash: self.hash.clone(),
+            timestamp: self.timestamp,
+            key_id: self.key_id.clone(),
+            algorithm: self.algorithm.clone(),
+        };
+        let verified = serde_json::to_vec(&payload).ok().and_then(|payload| {
+            let signature = BASE64.decode(&self.signature).ok()?;
+            let key = Ed25519PublicKey::from_base64(public_key).ok()?;
+            key.verify(&payload, &signature).ok()
+        });
+        match verified {
+            Some(()) => VerificationResult::valid(self.key_id.clone()),
+            None => VerificationResult::failed("Invalid signature".to_string()),
+        }
+    }
+}
+
+/// A key certified by the key that issued it, so signatures made with it
+/// are trusted as far as the issuer is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyCertificate {
+    /// Id of the certified key
+    pub key_id: String,
+    /// The certified key, base64 Ed25519
+    pub public_key: String,
+    /// Id of the issuing key
+    pub issuer: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Base64 signature of the issuer over the other fields
+    pub signature: String,
+}
+
+/// What the issuer of a certificate signs
+#[derive(Serialize)]
+struct CertificatePayload<'a> {
+    key_id: &'a str,
+    public_key: &'a str,
+    issuer: &'a str,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl KeyCertificate {
+    /// Certify `public_key` as `key_id` with the key `issuer_key` of id `issuer`
+    pub fn issue(
+        key_id: &str,
+        public_key: &Ed25519PublicKey,
+        issuer: &str,
+        issuer_key: &Ed25519PrivateKey,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Self> {
+        let mut certificate = Self {
+            key_id: key_id.to_string(),
+            public_key: public_key.to_base64(),
+            issuer: issuer.to_string(),
+            expires_at,
+            signature: String::new(),
+        };
+        certificate.signature = BASE64.encode(issuer_key.sign(&certificate.payload()?)?);
+        Ok(certificate)
+    }
+
+    /// Whether the base64 Ed25519 key `issuer_key` signed this certificate
+    pub fn check(&self, issuer_key: &str) -> bool {
+        let verified = self.payload().ok().and_then(|payload| {
+            let signature = BASE64.decode(&self.signature).ok()?;
+            let key = Ed25519PublicKey::from_base64(issuer_key).ok()?;
+            key.verify(&payload, &signature).ok()
+        });
+        verified.is_some()
+    }
+
+    fn payload(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(&CertificatePayload {
+            key_id: &self.key_id,
+            public_key: &self.public_key,
+            issuer: &self.issuer,
+            expires_at: self.expires_at,
+        })
+        .context("Failed to serialize certificate payload")
+    }
 }
 
 /// Signature metadata
@@ -0,0 +1,598 @@
+//! Plugins installed in the plugin directory
+//!
+//! Installing a plugin copies its file, and the detached `.sig` next to it if
+//! there is one, into the plugin directory as `NAME.EXT`, and records it in
+//! [`INDEX_FILE`] there with its version, where it came from and whether it is
+//! enabled. The shell loads the enabled plugins at startup, along with plugin
+//! files put in the directory by hand. An update moves the file it replaces
+//! to `.cache/NAME`; removing a plugin deletes its file, signature, cached
+//! files and pinned key.
+//!
+//! A signature is checked against the key pinned for the plugin in
+//! `keys/NAME.pub` and the keys of the keyring `keyring.toml` there, see
+//! [`crate::trust`]. Plugins whose signature does not verify are not
+//! installed. Plugins signed with a revoked key are neither installed nor
+//! loaded, unless the store allows revoked keys; a plugin installed,
+//! updated or enabled that way keeps being loaded.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::manager::{describe_plugin, plugin_dir, PluginManager};
+#[cfg(feature = "remote-plugins")]
+use crate::remote::fetch_url;
+use crate::PluginMetadata;
+#[cfg(feature = "crypto-verification")]
+use crate::{
+    signature::PluginSignature,
+    trust::{Keyring, Trust, Verdict, KEYRING_FILE},
+};
+
+/// Index of the installed plugins, in the plugin directory
+pub const INDEX_FILE: &str = "installed.toml";
+
+/// Extensions of the files that can be installed as plugins
+const PLUGIN_EXTENSIONS: [&str; 4] = ["wasm", "so", "dylib", "dll"];
+
+/// A plugin installed in the plugin directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    /// Name from the plugin's manifest
+    #[serde(skip)]
+    pub name: String,
+    pub version: String,
+    /// File in the plugin directory
+    pub file: String,
+    /// Path or URL it was installed from
+    pub source: String,
+    /// Whether the shell loads it at startup
+    pub enabled: bool,
+    /// When it was installed or last updated, in RFC 3339
+    pub installed: String,
+    /// Whether it is loaded even if signed with a revoked key
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_revoked: bool,
+}
+
+impl InstalledPlugin {
+    /// Id of the plugin once loaded
+    pub fn id(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+/// What the detached signature of a plugin shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// There is no `.sig` file
+    Unsigned,
+    /// Signed with the trusted key of this id
+    Verified(String),
+    /// The signature does not verify, for this reason
+    Invalid(String),
+    /// Signed with a trusted key, but `key` on the way to it is revoked
+    Revoked { key: String, reason: String },
+    /// Built without signature verification
+    Unchecked,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Unsigned => f.write_str("unsigned"),
+            SignatureStatus::Verified(key_id) => write!(f, "verified ({key_id})"),
+            SignatureStatus::Invalid(reason) => write!(f, "invalid: {reason}"),
+            SignatureStatus::Revoked { key, reason } if reason.is_empty() => {
+                write!(f, "revoked ({key})")
+            }
+            SignatureStatus::Revoked { key, reason } => write!(f, "revoked ({key}: {reason})"),
+            SignatureStatus::Unchecked => f.write_str("unchecked"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default)]
+    plugins: BTreeMap<String, InstalledPlugin>,
+}
+
+/// A plugin read from where it is installed from
+struct Fetched {
+    file_name: String,
+    bytes: Vec<u8>,
+    signature: Option<Vec<u8>>,
+}
+
+/// The plugins installed in a plugin directory
+#[derive(Debug, Clone)]
+pub struct PluginStore {
+    dir: PathBuf,
+    allow_revoked: bool,
+    #[cfg(feature = "crypto-verification")]
+    trust: Trust,
+}
+
+impl PluginStore {
+    /// The store in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            #[cfg(feature = "crypto-verification")]
+            trust: Trust::new(
+                Keyring::new(dir.join(KEYRING_FILE)),
+                dir.join(".cache").join(".trust"),
+            ),
+            dir,
+            allow_revoked: false,
+        }
+    }
+
+    /// Install, update, enable and load plugins even when they are signed
+    /// with a revoked key, recording that for those installed, updated or
+    /// enabled
+    pub fn allow_revoked(mut self, allow: bool) -> Self {
+        self.allow_revoked = allow;
+        self
+    }
+
+    /// Check for revoked keys in the revocation list at `url`, or in none
+    #[cfg(feature = "crypto-verification")]
+    pub fn with_revocation_url(mut self, url: Option<String>) -> Self {
+        self.trust.set_revocation_url(url);
+        self
+    }
+
+    /// The keyring signatures are checked against
+    #[cfg(feature = "crypto-verification")]
+    pub fn keyring(&self) -> &Keyring {
+        self.trust.keyring()
+    }
+
+    /// The store in the directory the shell loads plugins from
+    pub fn open() -> Result<Self> {
+        plugin_dir()
+            .map(Self::new)
+            .ok_or_else(|| anyhow!("cannot find the plugin directory"))
+    }
+
+    /// Directory of the store
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Installed plugins, in name order
+    pub fn list(&self) -> Result<Vec<InstalledPlugin>> {
+        Ok(self.index()?.plugins.into_values().collect())
+    }
+
+    /// The installed plugin named `name`
+    pub fn get(&self, name: &str) -> Result<InstalledPlugin> {
+        self.index()?
+            .plugins
+            .remove(name)
+            .ok_or_else(|| not_installed(name))
+    }
+
+    /// WASM plugin files to load at startup, in name order: those of enabled
+    /// plugins and those not installed through the store
+    pub fn startup_files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match self.dir.read_dir() {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", self.dir.display())),
+        };
+        let disabled: HashSet<String> = self
+            .index()?
+            .plugins
+            .into_values()
+            .filter(|plugin| !plugin.enabled)
+            .map(|plugin| plugin.file)
+            .collect();
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| !disabled.contains(name))
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Refuse to load the plugin file `file` when it is signed with a
+    /// revoked key, unless it was installed, updated or enabled allowing that
+    pub fn check_revoked(&self, file: &Path) -> Result<()> {
+        let Ok(signature) = fs::read(file.with_extension("sig")) else {
+            return Ok(());
+        };
+        let file_name = file.file_name().and_then(|name| name.to_str());
+        let installed = self
+            .index()?
+            .plugins
+            .into_values()
+            .find(|plugin| Some(plugin.file.as_str()) == file_name);
+        if self.allow_revoked
+            || installed
+                .as_ref()
+                .is_some_and(|plugin| plugin.allow_revoked)
+        {
+            return Ok(());
+        }
+        let name = match installed {
+            Some(plugin) => plugin.name,
+            None => file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let bytes = fs::read(file).with_context(|| format!("cannot read {}", file.display()))?;
+        match self.check_signature(&name, None, &bytes, &signature) {
+            SignatureStatus::Revoked { key, reason } => Err(revoked(&name, &key, &reason)),
+            _ => Ok(()),
+        }
+    }
+
+    /// What the signature of `plugin` shows
+    pub fn signature_status(&self, plugin: &InstalledPlugin) -> SignatureStatus {
+        let path = self.dir.join(&plugin.file);
+        let Ok(signature) = fs::read(path.with_extension("sig")) else {
+            return SignatureStatus::Unsigned;
+        };
+        match fs::read(&path) {
+            Ok(bytes) => self.check_signature(&plugin.name, None, &bytes, &signature),
+            Err(e) => SignatureStatus::Invalid(e.to_string()),
+        }
+    }
+
+    /// Install the plugin at `source`, a path or an `http(s)` URL, pinning
+    /// `key` (a base64 Ed25519 public key) to verify it and its updates
+    pub fn install(
+        &self,
+        manager: &PluginManager,
+        source: &str,
+        key: Option<&str>,
+    ) -> Result<InstalledPlugin> {
+        if key.is_some_and(|key| !crate::keys::is_valid_ed25519_pubkey_b64(key.trim())) {
+            bail!("invalid public key: expected a base64 Ed25519 key");
+        }
+        let fetched = fetch(source)?;
+        let metadata = inspect(manager, &fetched)?;
+        let name = metadata.name.clone();
+        let mut index = self.index()?;
+        if index.plugins.contains_key(&name) {
+            bail!("{name} is already installed; update it instead");
+        }
+        self.verify(&name, key, &fetched)?;
+
+        let file = self.write_plugin(&name, &fetched)?;
+        if let Some(key) = key {
+            let path = self.key_file(&name);
+            fs::create_dir_all(self.dir.join("keys"))
+                .and_then(|()| fs::write(&path, format!("{}\n", key.trim())))
+                .with_context(|| format!("cannot write {}", path.display()))?;
+        }
+        let plugin = InstalledPlugin {
+            name: name.clone(),
+            version: metadata.version,
+            file,
+            source: source.to_string(),
+            enabled: true,
+            installed: Utc::now().to_rfc3339(),
+            allow_revoked: self.allow_revoked,
+        };
+        index.plugins.insert(name, plugin.clone());
+        self.save(&index)?;
+        Ok(plugin)
+    }
+
+    /// Enable or disable the plugin named `name`; enabling it in a store
+    /// that allows revoked keys allows them for the plugin from then on
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<InstalledPlugin> {
+        let mut index = self.index()?;
+        let plugin = index
+            .plugins
+            .get_mut(name)
+            .ok_or_else(|| not_installed(name))?;
+        plugin.enabled = enabled;
+        plugin.allow_revoked |= enabled && self.allow_revoked;
+        let plugin = plugin.clone();
+        self.save(&index)?;
+        Ok(plugin)
+    }
+
+    /// Update the plugin named `name` from `source`, by default where it was
+    /// installed from, returning the version it had and the plugin now
+    ///
+    /// The plugin is left alone when `source` has the version installed, and
+    /// an older version is refused, unless `force` is given.
+    pub fn update(
+        &self,
+        manager: &PluginManager,
+        name: &str,
+        source: Option<&str>,
+        force: bool,
+    ) -> Result<(String, InstalledPlugin)> {
+        let mut index = self.index()?;
+        let old = index
+            .plugins
+            .get(name)
+            .cloned()
+            .ok_or_else(|| not_installed(name))?;
+        let source = source.unwrap_or(&old.source);
+        let fetched = fetch(source)?;
+        let metadata = inspect(manager, &fetched)?;
+        if metadata.name != name {
+            bail!("{source} is plugin {}, not {name}", metadata.name);
+        }
+        let installed = Version::parse(&old.version)
+            .with_context(|| format!("invalid installed version of {name}"))?;
+        let offered = Version::parse(&metadata.version)?;
+        if !force {
+            if offered == installed {
+                return Ok((old.version.clone(), old));
+            }
+            if offered < installed {
+                bail!("{source} has {name} {offered}, older than the installed {installed}");
+            }
+        }
+        self.verify(name, None, &fetched)?;
+
+        let cache = self.cache_dir(name);
+        fs::create_dir_all(&cache).with_context(|| format!("cannot create {}", cache.display()))?;
+        let current = self.dir.join(&old.file);
+        let extension = current
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("wasm");
+        for (from, to) in [
+            (
+                current.clone(),
+                format!("{name}-{}.{extension}", old.version),
+            ),
+            (
+                current.with_extension("sig"),
+                format!("{name}-{}.sig", old.version),
+            ),
+        ] {
+            match fs::rename(&from, cache.join(to)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("cannot move {}", from.display()));
+                }
+                _ => {}
+            }
+        }
+
+        let file = self.write_plugin(name, &fetched)?;
+        let plugin = InstalledPlugin {
+            name: name.to_string(),
+            version: metadata.version,
+            file,
+            source: source.to_string(),
+            enabled: old.enabled,
+            installed: Utc::now().to_rfc3339(),
+            allow_revoked: self.allow_revoked,
+        };
+        index.plugins.insert(name.to_string(), plugin.clone());
+        self.save(&index)?;
+        Ok((old.version, plugin))
+    }
+
+    /// Uninstall the plugin named `name`, deleting its file, signature,
+    /// cached files and pinned key
+    pub fn remove(&self, name: &str) -> Result<InstalledPlugin> {
+        let mut index = self.index()?;
+        let plugin = index
+            .plugins
+            .remove(name)
+            .ok_or_else(|| not_installed(name))?;
+        let path = self.dir.join(&plugin.file);
+        remove_if_exists(&path)?;
+        remove_if_exists(&path.with_extension("sig"))?;
+        remove_if_exists(&self.key_file(name))?;
+        let cache = self.cache_dir(name);
+        match fs::remove_dir_all(&cache) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("cannot remove {}", cache.display()));
+            }
+            _ => {}
+        }
+        self.save(&index)?;
+        Ok(plugin)
+    }
+
+    fn index(&self) -> Result<Index> {
+        let path = self.dir.join(INDEX_FILE);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Index::default()),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+        };
+        let mut index: Index =
+            toml::from_str(&text).with_context(|| format!("invalid {}", path.display()))?;
+        for (name, plugin) in &mut index.plugins {
+            plugin.name = name.clone();
+        }
+        Ok(index)
+    }
+
+    fn save(&self, index: &Index) -> Result<()> {
+        let path = self.dir.join(INDEX_FILE);
+        let text = toml::to_string(index).context("cannot serialize the plugin index")?;
+        fs::write(&path, text).with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    /// Write the plugin file named after `name`, and its signature, returning
+    /// the file's name
+    fn write_plugin(&self, name: &str, fetched: &Fetched) -> Result<String> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("cannot create {}", self.dir.display()))?;
+        let extension = Path::new(&fetched.file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("wasm");
+        let file = format!("{name}.{extension}");
+        let path = self.dir.join(&file);
+        fs::write(&path, &fetched.bytes)
+            .with_context(|| format!("cannot write {}", path.display()))?;
+        let signature = path.with_extension("sig");
+        match &fetched.signature {
+            Some(bytes) => fs::write(&signature, bytes)
+                .with_context(|| format!("cannot write {}", signature.display()))?,
+            None => remove_if_exists(&signature)?,
+        }
+        Ok(file)
+    }
+
+    /// Refuse a plugin whose signature does not verify, or is made with a
+    /// revoked key unless the store allows that
+    fn verify(&self, name: &str, key: Option<&str>, fetched: &Fetched) -> Result<()> {
+        let Some(signature) = &fetched.signature else {
+            return Ok(());
+        };
+        match self.check_signature(name, key, &fetched.bytes, signature) {
+            SignatureStatus::Invalid(reason) => bail!("signature of {name} is invalid: {reason}"),
+            SignatureStatus::Revoked { key, reason } if !self.allow_revoked => {
+                Err(revoked(name, &key, &reason))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check `signature` of `bytes` against `key`, or the key pinned for
+    /// `name`, and the keyring
+    #[cfg(feature = "crypto-verification")]
+    fn check_signature(
+        &self,
+        name: &str,
+        key: Option<&str>,
+        bytes: &[u8],
+        signature: &[u8],
+    ) -> SignatureStatus {
+        let signature: PluginSignature = match serde_json::from_slice(signature) {
+            Ok(signature) => signature,
+            Err(e) => return SignatureStatus::Invalid(format!("malformed signature: {e}")),
+        };
+        let pinned = match key {
+            Some(key) => Some(key.to_string()),
+            None => fs::read_to_string(self.key_file(name)).ok(),
+        };
+        match self.trust.check(&signature, bytes, pinned.as_deref()) {
+            Verdict::Verified(key_id) => SignatureStatus::Verified(key_id),
+            Verdict::Invalid(reason) => SignatureStatus::Invalid(reason),
+            Verdict::Revoked { key, reason } => SignatureStatus::Revoked { key, reason },
+        }
+    }
+
+    #[cfg(not(feature = "crypto-verification"))]
+    fn check_signature(
+        &self,
+        _name: &str,
+        _key: Option<&str>,
+        _bytes: &[u8],
+        _signature: &[u8],
+    ) -> SignatureStatus {
+        SignatureStatus::Unchecked
+    }
+
+    fn key_file(&self, name: &str) -> PathBuf {
+        self.dir.join("keys").join(format!("{name}.pub"))
+    }
+
+    fn cache_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(".cache").join(name)
+    }
+}
+
+fn not_installed(name: &str) -> anyhow::Error {
+    anyhow!("{name} is not installed")
+}
+
+fn revoked(name: &str, key: &str, reason: &str) -> anyhow::Error {
+    if reason.is_empty() {
+        anyhow!("{name} is signed with revoked key {key}")
+    } else {
+        anyhow!("{name} is signed with revoked key {key}: {reason}")
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("cannot remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Describe and validate a fetched plugin
+fn inspect(manager: &PluginManager, fetched: &Fetched) -> Result<PluginMetadata> {
+    let path = Path::new(&fetched.file_name);
+    if !path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PLUGIN_EXTENSIONS.contains(&ext))
+    {
+        bail!("{} is not a plugin file", fetched.file_name);
+    }
+    let metadata = describe_plugin(path, &fetched.bytes)?;
+    manager.validate_plugin_metadata(&metadata)?;
+    if metadata.name.starts_with('.') || metadata.name.contains(['/', '\\']) {
+        bail!("invalid plugin name: {}", metadata.name);
+    }
+    Ok(metadata)
+}
+
+/// Read the plugin at `source` and the signature next to it
+fn fetch(source: &str) -> Result<Fetched> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        return download(source);
+    }
+    let path = Path::new(source);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("{source} is not a plugin file"))?
+        .to_string();
+    let bytes = fs::read(path).with_context(|| format!("cannot read {source}"))?;
+    let signature = match fs::read(path.with_extension("sig")) {
+        Ok(signature) => Some(signature),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("cannot read the signature of {source}")),
+    };
+    Ok(Fetched {
+        file_name,
+        bytes,
+        signature,
+    })
+}
+
+#[cfg(feature = "remote-plugins")]
+fn download(url: &str) -> Result<Fetched> {
+    let base = url.split(['?', '#']).next().unwrap_or(url);
+    let file_name = base.rsplit('/').next().unwrap_or_default().to_string();
+    let signature_url = match base.rfind('.') {
+        Some(dot) if dot > base.rfind('/').unwrap_or(0) => format!("{}.sig", &base[..dot]),
+        _ => format!("{base}.sig"),
+    };
+    let bytes = fetch_url(url, &mut |_, _| {})?.ok_or_else(|| anyhow!("{url}: not found"))?;
+    Ok(Fetched {
+        file_name,
+        bytes,
+        signature: fetch_url(&signature_url, &mut |_, _| {})?,
+    })
+}
+
+#[cfg(not(feature = "remote-plugins"))]
+fn download(url: &str) -> Result<Fetched> {
+    bail!("cannot download {url}: built without remote plugin support")
+}
@@ -0,0 +1,696 @@
+//! Testing plugins without a shell
+//!
+//! [`MockPluginManager`] loads WASI plugins as the shell does, answering
+//! their requests for capabilities as the test tells it to, and runs their
+//! commands, completers and prompt segments in a scratch directory. Its
+//! [`CapabilityRecorder`] keeps what each plugin asked for and what it was
+//! granted and denied. [`assert_golden`] compares output with a file kept
+//! next to the tests, rewriting the file instead when `NXSH_UPDATE_GOLDEN`
+//! is set. [`verify`] runs the ABI and metadata checks of `plugin verify`.
+//!
+//! ```no_run
+//! use nxsh_plugin::testing::{assert_golden_output, verify, MockPluginManager};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let plugin = "target/wasm32-wasip1/release/greeter.wasm";
+//! assert!(verify(plugin)?.passed());
+//! let mut plugins = MockPluginManager::new().await?.deny("network_request");
+//! let id = plugins.load(plugin).await?;
+//! assert_eq!(plugins.recorder().denied(&id), ["network_request"]);
+//! let output = plugins.run("greet", &["world"]).await?;
+//! assert_golden_output("tests/golden/greet.txt", &output);
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use crate::consent::{
+    CapabilityConsent, ConsentPrompt, ConsentRequest, Decision, PermissionGrants,
+};
+use crate::manager::{describe_plugin, runtime_metadata};
+use crate::runtime::{self, CommandOutput, WasiPluginRuntime};
+use crate::{wasi, PluginEvent, PluginEventHandler, PluginManager};
+
+/// Set to rewrite golden files with the output they are compared with
+pub const UPDATE_GOLDEN: &str = "NXSH_UPDATE_GOLDEN";
+
+/// What happened to a capability a plugin asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityRecord {
+    /// The plugin asked for it when being loaded for the first time
+    Requested { plugin: String, capability: String },
+    /// It was granted to the plugin with ID `plugin_id`
+    Granted {
+        plugin_id: String,
+        capability: String,
+    },
+    /// It was denied the plugin with ID `plugin_id`, with why
+    Denied {
+        plugin_id: String,
+        capability: String,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    /// Decisions on particular capabilities
+    decisions: HashMap<String, Decision>,
+    /// Decision on the others
+    default: Option<Decision>,
+    records: Vec<CapabilityRecord>,
+}
+
+/// Answers requests for capabilities as a test says and records what
+/// plugins asked for and got
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityRecorder {
+    inner: Arc<Mutex<Recorded>>,
+}
+
+impl CapabilityRecorder {
+    /// A recorder granting every capability until told otherwise
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `decision` when asked for `capability`
+    pub fn decide(&self, capability: &str, decision: Decision) {
+        self.lock()
+            .decisions
+            .insert(capability.to_string(), decision);
+    }
+
+    /// Answer `decision` for capabilities without a decision of their own
+    pub fn decide_others(&self, decision: Decision) {
+        self.lock().default = Some(decision);
+    }
+
+    /// Everything recorded, in order
+    pub fn records(&self) -> Vec<CapabilityRecord> {
+        self.lock().records.clone()
+    }
+
+    /// The capabilities plugin `name` asked for
+    pub fn requested(&self, name: &str) -> Vec<String> {
+        self.lock()
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                CapabilityRecord::Requested { plugin, capability } if plugin == name => {
+                    Some(capability.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The capabilities granted to the plugin with ID `plugin_id`
+    pub fn granted(&self, plugin_id: &str) -> Vec<String> {
+        self.lock()
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                CapabilityRecord::Granted {
+                    plugin_id: id,
+                    capability,
+                } if id == plugin_id => Some(capability.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The capabilities denied the plugin with ID `plugin_id`
+    pub fn denied(&self, plugin_id: &str) -> Vec<String> {
+        self.lock()
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                CapabilityRecord::Denied {
+                    plugin_id: id,
+                    capability,
+                    ..
+                } if id == plugin_id => Some(capability.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recorded> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ConsentPrompt for CapabilityRecorder {
+    fn ask(&self, request: &ConsentRequest<'_>) -> Option<Vec<Decision>> {
+        let mut recorded = self.lock();
+        let mut decisions = Vec::new();
+        for capability in request.capabilities {
+            recorded.records.push(CapabilityRecord::Requested {
+                plugin: request.name.to_string(),
+                capability: capability.clone(),
+            });
+            let decision = recorded.decisions.get(capability).copied();
+            decisions.push(decision.or(recorded.default).unwrap_or(Decision::Granted));
+        }
+        Some(decisions)
+    }
+}
+
+impl PluginEventHandler for CapabilityRecorder {
+    fn handle_event(
+        &self,
+        event: PluginEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        let record = match event {
+            PluginEvent::PermissionGranted {
+                plugin_id,
+                capability,
+            } => Some(CapabilityRecord::Granted {
+                plugin_id,
+                capability,
+            }),
+            PluginEvent::PermissionDenied {
+                plugin_id,
+                capability,
+                reason,
+            } => Some(CapabilityRecord::Denied {
+                plugin_id,
+                capability,
+                reason,
+            }),
+            _ => None,
+        };
+        if let Some(record) = record {
+            self.lock().records.push(record);
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A [`PluginManager`] for tests: it loads plugins like the shell's, with
+/// the capabilities its [`CapabilityRecorder`] grants, and runs what they
+/// provide in a scratch directory removed when it is dropped
+pub struct MockPluginManager {
+    manager: PluginManager,
+    runtime: Arc<WasiPluginRuntime>,
+    recorder: CapabilityRecorder,
+    scratch: PathBuf,
+    cwd: PathBuf,
+    env: HashMap<String, String>,
+}
+
+impl MockPluginManager {
+    /// A manager granting plugins every capability they ask for, with an
+    /// empty environment
+    pub async fn new() -> Result<Self> {
+        let scratch =
+            std::env::temp_dir().join(format!("nxsh-plugin-test-{}", uuid::Uuid::new_v4()));
+        let cwd = scratch.join("cwd");
+        fs::create_dir_all(&cwd).with_context(|| format!("cannot create {}", cwd.display()))?;
+
+        let mut manager = PluginManager::new();
+        manager.initialize_runtimes().await?;
+        let runtime = manager
+            .wasi_runtime()
+            .cloned()
+            .context("WASI runtime unavailable")?;
+        let recorder = CapabilityRecorder::new();
+        let grants = PermissionGrants::new(scratch.join("plugin-permissions.toml"));
+        manager.set_consent(CapabilityConsent::new(
+            grants,
+            Some(Box::new(recorder.clone())),
+        ));
+        manager.add_event_handler(Box::new(recorder.clone()));
+        Ok(Self {
+            manager,
+            runtime,
+            recorder,
+            scratch,
+            cwd,
+            env: HashMap::new(),
+        })
+    }
+
+    /// Grant `capability` to plugins asking for it
+    pub fn grant(self, capability: &str) -> Self {
+        self.recorder.decide(capability, Decision::Granted);
+        self
+    }
+
+    /// Deny `capability` to plugins asking for it
+    pub fn deny(self, capability: &str) -> Self {
+        self.recorder.decide(capability, Decision::Denied);
+        self
+    }
+
+    /// Deny plugins the capabilities not granted with [`grant`](Self::grant)
+    pub fn deny_others(self) -> Self {
+        self.recorder.decide_others(Decision::Denied);
+        self
+    }
+
+    /// Set the environment variable `name`, seen by plugins granted `env_read`
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.env.insert(name.to_string(), value.to_string());
+    }
+
+    /// The directory plugins run in, empty to start with
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// What plugins asked for and were granted and denied
+    pub fn recorder(&self) -> &CapabilityRecorder {
+        &self.recorder
+    }
+
+    /// The manager the plugins are loaded by
+    pub fn manager(&mut self) -> &mut PluginManager {
+        &mut self.manager
+    }
+
+    /// Load the plugin at `path`, returning its ID
+    pub async fn load(&mut self, path: impl AsRef<Path>) -> Result<String> {
+        self.manager.load_plugin(path).await
+    }
+
+    /// Load the plugin `bytes`, such as a module built with
+    /// [`embed_manifest`], as if it were the file `file_name`
+    pub async fn load_bytes(&mut self, file_name: &str, bytes: &[u8]) -> Result<String> {
+        let path = self.scratch.join(file_name);
+        fs::write(&path, bytes).with_context(|| format!("cannot write {}", path.display()))?;
+        self.load(&path).await
+    }
+
+    /// Unload the plugin with ID `plugin_id`
+    pub async fn unload(&mut self, plugin_id: &str) -> Result<()> {
+        self.manager.unload_plugin(plugin_id).await
+    }
+
+    /// The commands the loaded plugins provide, in order
+    pub async fn commands(&self) -> Vec<String> {
+        self.runtime.commands().await
+    }
+
+    /// Run the plugin command `command` with `args`
+    pub async fn run(&self, command: &str, args: &[&str]) -> Result<CommandOutput> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        self.runtime
+            .run_command(command, &args, &self.env, &self.cwd)
+            .await
+    }
+
+    /// Candidates for the word under the cursor at byte `cursor` of `line`,
+    /// from the plugin completing `command`
+    pub async fn complete(&self, command: &str, line: &str, cursor: usize) -> Result<Vec<String>> {
+        self.runtime
+            .complete(command, line, cursor, &self.env, &self.cwd)
+            .await
+    }
+
+    /// The text of the prompt segment `name`
+    pub async fn render_segment(&self, name: &str) -> Result<String> {
+        self.runtime
+            .render_segment(name, &self.env, &self.cwd)
+            .await
+    }
+}
+
+impl Drop for MockPluginManager {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.scratch);
+    }
+}
+
+/// `wasm` with `manifest`, a JSON [`Manifest`](runtime::Manifest), in its
+/// [`MANIFEST_SECTION`](runtime::MANIFEST_SECTION)
+pub fn embed_manifest(wasm: &[u8], manifest: &str) -> Vec<u8> {
+    let name = runtime::MANIFEST_SECTION.as_bytes();
+    let mut section = leb128(name.len());
+    section.extend_from_slice(name);
+    section.extend_from_slice(manifest.as_bytes());
+    let mut module = wasm.to_vec();
+    module.push(0);
+    module.extend(leb128(section.len()));
+    module.extend(section);
+    module
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// `output` as golden files hold it: the status, then what was written to
+/// each stream
+pub fn render_output(output: &CommandOutput) -> String {
+    let mut text = format!("status: {}\n", output.status);
+    for (name, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        text.push_str(&format!("--- {name}\n"));
+        text.push_str(&String::from_utf8_lossy(bytes));
+        if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+            text.push_str("\n\\ no newline at end\n");
+        }
+    }
+    text
+}
+
+/// Panic unless `actual` is what the golden file at `path` holds; with
+/// [`UPDATE_GOLDEN`] set, write `actual` to the file instead
+pub fn assert_golden(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN).is_some_and(|value| !value.is_empty()) {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("cannot create {}: {e}", dir.display()));
+        }
+        fs::write(path, actual).unwrap_or_else(|e| panic!("cannot write {}: {e}", path.display()));
+        return;
+    }
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read golden file {}: {e}; run with {UPDATE_GOLDEN}=1 to create it",
+            path.display()
+        )
+    });
+    if expected == actual {
+        return;
+    }
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (e, a) => panic!(
+                "{} differs at line {line}\nexpected: {}\n  actual: {}\n\
+                 run with {UPDATE_GOLDEN}=1 to accept the new output",
+                path.display(),
+                e.unwrap_or("<end of file>"),
+                a.unwrap_or("<end of output>"),
+            ),
+        }
+    }
+}
+
+/// Panic unless `output`, as [`render_output`] shows it, is what the golden
+/// file at `path` holds
+pub fn assert_golden_output(path: impl AsRef<Path>, output: &CommandOutput) {
+    assert_golden(path, &render_output(output));
+}
+
+/// How a conformance check came out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// The plugin loads, but something will not work as its author expects
+    Warn,
+    /// The plugin cannot be loaded
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "FAIL",
+        })
+    }
+}
+
+/// One conformance check of a plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// The conformance checks of a plugin, in the order they ran
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Whether no check failed
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+
+    /// The check named `name`
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or(0);
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{:4}  {:width$}  {}",
+                check.status.to_string(),
+                check.name,
+                check.detail
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that the plugin at `path` is one the shell can load: a `.wasm`
+/// module for its valid manifest and metadata, the WASI functions it
+/// imports and the signatures of the functions it exports, or a native
+/// library for the functions it must export. Only a plugin file that
+/// cannot be read is an error.
+pub fn verify(path: impl AsRef<Path>) -> Result<Report> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let mut report = Report::default();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("wasm") => verify_wasm(path, &bytes, &mut report),
+        Some("so" | "dylib" | "dll") => verify_native(path, &mut report),
+        _ => report.push(
+            "format",
+            CheckStatus::Fail,
+            "not a plugin: expected a .wasm, .so, .dylib or .dll file",
+        ),
+    }
+    Ok(report)
+}
+
+fn verify_wasm(path: &Path, bytes: &[u8], report: &mut Report) {
+    use CheckStatus::{Fail, Pass, Warn};
+
+    let engine = wasmi::Engine::default();
+    let module = match wasmi::Module::new(&engine, bytes) {
+        Ok(module) => {
+            report.push("module", Pass, "valid WebAssembly");
+            module
+        }
+        Err(e) => return report.push("module", Fail, format!("not valid WebAssembly: {e}")),
+    };
+
+    match runtime::read_manifest(bytes) {
+        Ok(Some(_)) => report.push("manifest", Pass, runtime::MANIFEST_SECTION),
+        Ok(None) => report.push(
+            "manifest",
+            Warn,
+            format!(
+                "no {} section; the plugin is named after its file and gets no capabilities",
+                runtime::MANIFEST_SECTION
+            ),
+        ),
+        Err(e) => return report.push("manifest", Fail, format!("{e:#}")),
+    }
+
+    let metadata = match describe_plugin(path, bytes) {
+        Ok(metadata) => metadata,
+        Err(e) => return report.push("metadata", Fail, format!("{e:#}")),
+    };
+    match PluginManager::new().validate_plugin_metadata(&metadata) {
+        Ok(()) => report.push(
+            "metadata",
+            Pass,
+            format!("{} {}", metadata.name, metadata.version),
+        ),
+        Err(e) => report.push("metadata", Fail, format!("{e:#}")),
+    }
+
+    let unavailable: Vec<String> = metadata
+        .capabilities
+        .iter()
+        .filter_map(|capability| runtime::check_capability(capability).err())
+        .map(|e| e.to_string())
+        .collect();
+    match (unavailable.is_empty(), metadata.capabilities.is_empty()) {
+        (false, _) => report.push("capabilities", Fail, unavailable.join("; ")),
+        (true, true) => report.push("capabilities", Pass, "none"),
+        (true, false) => report.push("capabilities", Pass, metadata.capabilities.join(", ")),
+    }
+
+    let mut foreign = Vec::new();
+    let mut stubbed = Vec::new();
+    for import in module.imports() {
+        let is_func = matches!(import.ty(), wasmi::ExternType::Func(_));
+        if import.module() != wasi::MODULE || !is_func {
+            foreign.push(format!("{}::{}", import.module(), import.name()));
+        } else if !wasi::IMPLEMENTED.contains(&import.name()) {
+            stubbed.push(import.name().to_string());
+        }
+    }
+    if !foreign.is_empty() {
+        report.push(
+            "imports",
+            Fail,
+            format!("imports what plugins cannot use: {}", foreign.join(", ")),
+        );
+    } else if !stubbed.is_empty() {
+        report.push(
+            "imports",
+            Warn,
+            format!("not provided, failing with ENOSYS: {}", stubbed.join(", ")),
+        );
+    } else {
+        report.push("imports", Pass, wasi::MODULE);
+    }
+
+    let runtime_metadata = match runtime_metadata(&metadata, bytes) {
+        Ok(runtime_metadata) => runtime_metadata,
+        Err(e) => return report.push("exports", Fail, format!("{e:#}")),
+    };
+    let validated = WasiPluginRuntime::new()
+        .and_then(|runtime| runtime.validate_plugin(&metadata.name, bytes, &runtime_metadata));
+    if let Err(e) = validated {
+        return report.push("exports", Fail, format!("{e:#}"));
+    }
+    // Everything the shell runs is called without arguments and may return
+    // an exit status
+    let mut exports: Vec<String> = runtime_metadata
+        .commands
+        .iter()
+        .map(|spec| spec.export.clone().unwrap_or_else(|| spec.name.clone()))
+        .chain(runtime_metadata.completers.iter().map(|c| c.export.clone()))
+        .chain(
+            runtime_metadata
+                .segments
+                .iter()
+                .map(|s| s.export().to_string()),
+        )
+        .collect();
+    exports.push("_start".to_string());
+    // A command named after the plugin may run `_start` instead
+    exports.retain(|export| module.get_export(export).is_some());
+    exports.sort();
+    exports.dedup();
+    let mut wrong = Vec::new();
+    for export in &exports {
+        if let Some(wasmi::ExternType::Func(ty)) = module.get_export(export) {
+            let results_ok = matches!(ty.results(), [] | [wasmi::core::ValType::I32]);
+            if !ty.params().is_empty() || !results_ok {
+                wrong.push(export.clone());
+            }
+        }
+    }
+    if wrong.is_empty() {
+        report.push("exports", Pass, exports.join(", "));
+    } else {
+        report.push(
+            "exports",
+            Fail,
+            format!(
+                "must take no parameters and return nothing or an i32: {}",
+                wrong.join(", ")
+            ),
+        );
+    }
+
+    if matches!(
+        module.get_export("memory"),
+        Some(wasmi::ExternType::Memory(_))
+    ) {
+        report.push("memory", Pass, "exported");
+    } else {
+        report.push(
+            "memory",
+            Warn,
+            "no memory exported as `memory`; WASI calls that pass buffers fail",
+        );
+    }
+}
+
+#[cfg(feature = "native-plugins")]
+fn verify_native(path: &Path, report: &mut Report) {
+    use CheckStatus::{Fail, Pass, Warn};
+
+    let library = match unsafe { libloading::Library::new(path) } {
+        Ok(library) => {
+            report.push("library", Pass, "loads");
+            library
+        }
+        Err(e) => return report.push("library", Fail, format!("cannot be loaded: {e}")),
+    };
+    let exports = |name: &[u8]| unsafe { library.get::<*const ()>(name) }.is_ok();
+    if exports(b"nxsh_plugin_init") {
+        report.push("init", Pass, "nxsh_plugin_init");
+    } else {
+        report.push(
+            "init",
+            Warn,
+            "no nxsh_plugin_init; the plugin registers nothing when loaded",
+        );
+    }
+    if exports(b"nxsh_plugin_execute") {
+        report.push("execute", Pass, "nxsh_plugin_execute");
+    } else {
+        report.push(
+            "execute",
+            Fail,
+            "no nxsh_plugin_execute; the plugin cannot run commands",
+        );
+    }
+}
+
+#[cfg(not(feature = "native-plugins"))]
+fn verify_native(_path: &Path, report: &mut Report) {
+    report.push(
+        "library",
+        CheckStatus::Fail,
+        "native plugins are not supported by this build",
+    );
+}
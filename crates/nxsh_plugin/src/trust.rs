@@ -0,0 +1,601 @@
+//! Trust in the keys plugins are signed with
+//!
+//! A [`Keyring`] holds the keys the user trusts, each at a [`TrustLevel`]: a
+//! `root` key signs plugins and certifies intermediate keys, a `signer` key
+//! only signs plugins, and an `untrusted` key is kept but not used. The
+//! official and community keys of [`crate::keys`] are root keys unless the
+//! keyring gives them another level.
+//!
+//! A plugin signed with an intermediate key carries the chain of
+//! [`KeyCertificate`](crate::signature::KeyCertificate)s from that key up to a root key, each certificate
+//! signed by the key above it. The signature is trusted when the chain
+//! reaches a root key of the keyring within [`MAX_CHAIN`] certificates, none
+//! of them expired.
+//!
+//! Revoked keys are listed by [`fingerprint`] in a [`RevocationList`]
+//! published at `$NXSH_PLUGIN_REVOCATION_URL`, by default [`REVOCATION_URL`]
+//! when the official key is configured, with the base64 Ed25519 signature of
+//! its bytes by a root key at the same URL with `.sig` appended:
+//!
+//! ```json
+//! {"revoked": [{"fingerprint": "sha256:1f0e…", "reason": "key leaked"}]}
+//! ```
+//!
+//! The list is fetched at most once a day and kept in the cache. When it
+//! cannot be fetched, or in offline mode (`NXSH_PLUGIN_OFFLINE=1`), the cached
+//! list is used until it is [`OFFLINE_GRACE`] old; after that, or with no
+//! cached list, every signature counts as made with a revoked key, since
+//! nothing shows otherwise. A signature is revoked when any key from the
+//! signing key up to the trusted key is.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::Utc;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::keys::{self, is_valid_ed25519_pubkey_b64};
+#[cfg(feature = "remote-plugins")]
+use crate::remote::fetch_url;
+use crate::signature::{Ed25519PublicKey, PluginSignature};
+
+/// The keyring, in the plugin directory
+pub const KEYRING_FILE: &str = "keyring.toml";
+
+/// Where the revocation list is published when the official key is used
+pub const REVOCATION_URL: &str = "https://plugins.nexusshell.org/revocations.json";
+
+/// Name of the cached revocation list
+pub const REVOCATION_FILE: &str = "revocations.json";
+
+/// Id of the official key in the keyring
+pub const OFFICIAL_KEY: &str = "official";
+
+/// Id of the community key in the keyring
+pub const COMMUNITY_KEY: &str = "community";
+
+/// Most certificates followed from a signing key to a root key
+pub const MAX_CHAIN: usize = 4;
+
+/// How long a cached revocation list is used before it is fetched again
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a cached revocation list is used when it cannot be fetched
+pub const OFFLINE_GRACE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// What a key in the keyring is trusted to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    /// Sign plugins and certify intermediate keys
+    Root,
+    /// Sign plugins
+    Signer,
+    /// Nothing
+    Untrusted,
+}
+
+impl TrustLevel {
+    /// Every level, most trusted first
+    pub const ALL: [TrustLevel; 3] = [TrustLevel::Root, TrustLevel::Signer, TrustLevel::Untrusted];
+
+    /// The level named `name`, such as `signer`
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+
+    /// Name the level is known by
+    pub fn name(self) -> &'static str {
+        match self {
+            TrustLevel::Root => "root",
+            TrustLevel::Signer => "signer",
+            TrustLevel::Untrusted => "untrusted",
+        }
+    }
+}
+
+impl fmt::Display for TrustLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A key in the keyring
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+    pub id: String,
+    /// Base64 Ed25519 public key
+    pub key: String,
+    pub level: TrustLevel,
+    /// Whether it is the official or community key rather than one added
+    pub builtin: bool,
+}
+
+impl TrustedKey {
+    /// Fingerprint of the key, as revocation lists name it
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key)
+    }
+}
+
+/// `sha256:` and the hex SHA-256 of the base64 public key `key` decoded
+pub fn fingerprint(key: &str) -> String {
+    let key = key.trim();
+    let bytes = BASE64
+        .decode(key)
+        .unwrap_or_else(|_| key.as_bytes().to_vec());
+    format!("sha256:{}", hex::encode(Sha256::digest(bytes)))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyringFile {
+    /// By key id
+    #[serde(default)]
+    keys: BTreeMap<String, KeyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyEntry {
+    /// Empty when the entry sets the level of a built-in key
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    key: String,
+    level: TrustLevel,
+}
+
+/// The keys trusted to sign plugins, kept in a keyring file
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    path: PathBuf,
+}
+
+impl Keyring {
+    /// The keyring in the file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// File the keyring is kept in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The keys, the built-in ones that are configured first and then the
+    /// added ones in id order
+    pub fn list(&self) -> Result<Vec<TrustedKey>> {
+        let file = self.read()?;
+        let mut trusted = Vec::new();
+        for (id, key) in [
+            (OFFICIAL_KEY, keys::load_official_pubkey_b64()),
+            (COMMUNITY_KEY, keys::load_community_pubkey_b64()),
+        ] {
+            if key.trim().is_empty() {
+                continue;
+            }
+            let level = file
+                .keys
+                .get(id)
+                .filter(|entry| entry.key.is_empty())
+                .map_or(TrustLevel::Root, |entry| entry.level);
+            trusted.push(TrustedKey {
+                id: id.to_string(),
+                key: key.trim().to_string(),
+                level,
+                builtin: true,
+            });
+        }
+        trusted.extend(
+            file.keys
+                .into_iter()
+                .filter(|(_, entry)| !entry.key.is_empty())
+                .map(|(id, entry)| TrustedKey {
+                    id,
+                    key: entry.key,
+                    level: entry.level,
+                    builtin: false,
+                }),
+        );
+        Ok(trusted)
+    }
+
+    /// The key of id `id`
+    pub fn get(&self, id: &str) -> Result<TrustedKey> {
+        self.list()?
+            .into_iter()
+            .find(|key| key.id == id)
+            .ok_or_else(|| anyhow!("{id}: no such key"))
+    }
+
+    /// Trust the base64 Ed25519 public key `key` as `id` at `level`
+    pub fn add(&self, id: &str, key: &str, level: TrustLevel) -> Result<TrustedKey> {
+        let key = key.trim();
+        if id.is_empty() || id.contains(char::is_whitespace) {
+            bail!("invalid key id: {id:?}");
+        }
+        if !is_valid_ed25519_pubkey_b64(key) {
+            bail!("invalid public key: expected a base64 Ed25519 key");
+        }
+        if self.list()?.iter().any(|trusted| trusted.id == id) {
+            bail!("{id} is already in the keyring");
+        }
+        let mut file = self.read()?;
+        file.keys.insert(
+            id.to_string(),
+            KeyEntry {
+                key: key.to_string(),
+                level,
+            },
+        );
+        self.save(&file)?;
+        Ok(TrustedKey {
+            id: id.to_string(),
+            key: key.to_string(),
+            level,
+            builtin: false,
+        })
+    }
+
+    /// Stop trusting the added key `id`
+    pub fn remove(&self, id: &str) -> Result<TrustedKey> {
+        let key = self.get(id)?;
+        if key.builtin {
+            bail!("{id} is built in; make it untrusted instead");
+        }
+        let mut file = self.read()?;
+        file.keys.remove(id);
+        self.save(&file)?;
+        Ok(key)
+    }
+
+    /// Trust the key `id` at `level`
+    pub fn set_level(&self, id: &str, level: TrustLevel) -> Result<TrustedKey> {
+        let mut key = self.get(id)?;
+        let mut file = self.read()?;
+        file.keys
+            .entry(id.to_string())
+            .or_insert_with(|| KeyEntry {
+                key: String::new(),
+                level,
+            })
+            .level = level;
+        self.save(&file)?;
+        key.level = level;
+        Ok(key)
+    }
+
+    fn read(&self) -> Result<KeyringFile> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(KeyringFile::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("cannot read {}", self.path.display()))
+            }
+        };
+        toml::from_str(&text).with_context(|| format!("invalid {}", self.path.display()))
+    }
+
+    fn save(&self, file: &KeyringFile) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+        let text = toml::to_string(file).context("cannot serialize the keyring")?;
+        fs::write(&self.path, text).with_context(|| format!("cannot write {}", self.path.display()))
+    }
+}
+
+/// Keys that must no longer be trusted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationList {
+    #[serde(default)]
+    pub revoked: Vec<RevokedKey>,
+}
+
+/// A revoked key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevokedKey {
+    /// See [`fingerprint`]
+    pub fingerprint: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl RevocationList {
+    /// The entry revoking the key of fingerprint `fingerprint`, if any
+    pub fn find(&self, fingerprint: &str) -> Option<&RevokedKey> {
+        self.revoked
+            .iter()
+            .find(|revoked| revoked.fingerprint == fingerprint)
+    }
+}
+
+/// What checking a plugin signature found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Signed with the key of this id, trusted and not revoked
+    Verified(String),
+    /// The signature does not verify with a trusted key, for this reason
+    Invalid(String),
+    /// The signature verifies, but `key` on the way to the trusted key is
+    /// revoked or cannot be shown not to be
+    Revoked { key: String, reason: String },
+}
+
+/// The revocation URL to use by default: `$NXSH_PLUGIN_REVOCATION_URL`,
+/// none when it is empty, or [`REVOCATION_URL`] when the official key is
+/// configured
+pub fn default_revocation_url() -> Option<String> {
+    match std::env::var("NXSH_PLUGIN_REVOCATION_URL") {
+        Ok(url) => Some(url.trim().to_string()).filter(|url| !url.is_empty()),
+        Err(_) => (!keys::load_official_pubkey_b64().trim().is_empty())
+            .then(|| REVOCATION_URL.to_string()),
+    }
+}
+
+/// Checks plugin signatures against a keyring and the revocation list
+#[derive(Debug, Clone)]
+pub struct Trust {
+    keyring: Keyring,
+    cache_dir: PathBuf,
+    revocation_url: Option<String>,
+    offline: bool,
+    /// The revocation list once read, or why there is none
+    revocations: OnceCell<Result<RevocationList, String>>,
+}
+
+impl Trust {
+    /// Check with `keyring`, keeping the revocation list of
+    /// [`default_revocation_url`] in `cache_dir`, offline when
+    /// `NXSH_PLUGIN_OFFLINE` is set
+    pub fn new(keyring: Keyring, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            keyring,
+            cache_dir: cache_dir.into(),
+            revocation_url: default_revocation_url(),
+            offline: std::env::var("NXSH_PLUGIN_OFFLINE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            revocations: OnceCell::new(),
+        }
+    }
+
+    /// Fetch the revocation list from `url`, or use none
+    pub fn set_revocation_url(&mut self, url: Option<String>) {
+        self.revocation_url = url;
+        self.revocations = OnceCell::new();
+    }
+
+    /// Use only the cached revocation list, never the network
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+        self.revocations = OnceCell::new();
+    }
+
+    /// The keyring checked against
+    pub fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Check `signature` of `bytes` against the keyring and `pinned`, a key
+    /// trusted to sign this plugin only
+    pub fn check(
+        &self,
+        signature: &PluginSignature,
+        bytes: &[u8],
+        pinned: Option<&str>,
+    ) -> Verdict {
+        let keys = match self.keyring.list() {
+            Ok(keys) => keys,
+            Err(e) => return Verdict::Invalid(format!("{e:#}")),
+        };
+        let pinned = pinned
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| TrustedKey {
+                id: "pinned".to_string(),
+                key: key.to_string(),
+                level: TrustLevel::Signer,
+                builtin: false,
+            });
+        let trusted: Vec<TrustedKey> = pinned
+            .into_iter()
+            .chain(keys.iter().cloned())
+            .filter(|key| key.level != TrustLevel::Untrusted)
+            .collect();
+
+        // The keys from the signing key up to the trusted one
+        let mut reason = format!("no trusted key for {}", signature.key_id);
+        let mut path = None;
+        for key in &trusted {
+            let result = signature.check(bytes, &key.key);
+            if result.valid {
+                path = Some(vec![(signature.key_id.clone(), key.key.clone())]);
+                break;
+            }
+            reason = result.error.unwrap_or(reason);
+        }
+        let path = match path {
+            Some(path) => path,
+            None if signature.chain.is_empty() => return Verdict::Invalid(reason),
+            None => match follow_chain(signature, bytes, &trusted) {
+                Ok(path) => path,
+                Err(reason) => return Verdict::Invalid(reason),
+            },
+        };
+
+        let revocations = self
+            .revocations
+            .get_or_init(|| self.read_revocations(&keys));
+        for (id, key) in path {
+            match revocations {
+                Ok(list) => {
+                    if let Some(revoked) = list.find(&fingerprint(&key)) {
+                        return Verdict::Revoked {
+                            key: id,
+                            reason: revoked.reason.clone(),
+                        };
+                    }
+                }
+                Err(reason) => {
+                    return Verdict::Revoked {
+                        key: id,
+                        reason: reason.clone(),
+                    }
+                }
+            }
+        }
+        Verdict::Verified(signature.key_id.clone())
+    }
+
+    /// The revocation list, fetched when the cached one is due for a
+    /// refresh, or why there is none to go by
+    fn read_revocations(&self, keys: &[TrustedKey]) -> Result<RevocationList, String> {
+        let Some(url) = &self.revocation_url else {
+            return Ok(RevocationList::default());
+        };
+        let roots: Vec<&str> = keys
+            .iter()
+            .filter(|key| key.level == TrustLevel::Root)
+            .map(|key| key.key.as_str())
+            .collect();
+        let path = self.cache_dir.join(REVOCATION_FILE);
+        let signature_path = self.cache_dir.join(format!("{REVOCATION_FILE}.sig"));
+        let age = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default()
+            });
+        let cached = || -> Result<RevocationList> {
+            let bytes = fs::read(&path)?;
+            let signature = fs::read_to_string(&signature_path)?;
+            verify_list(&bytes, &signature, &roots)
+        };
+        if age.is_some_and(|age| age < REFRESH_INTERVAL) {
+            if let Ok(list) = cached() {
+                return Ok(list);
+            }
+        }
+
+        let error = if self.offline {
+            "offline mode".to_string()
+        } else {
+            match fetch_list(url, &roots) {
+                Ok((bytes, signature, list)) => {
+                    let written = fs::create_dir_all(&self.cache_dir)
+                        .and_then(|()| fs::write(&path, &bytes))
+                        .and_then(|()| fs::write(&signature_path, &signature));
+                    if let Err(e) = written {
+                        log::warn!("Failed to cache the revocation list: {e}");
+                    }
+                    return Ok(list);
+                }
+                Err(e) => format!("{e:#}"),
+            }
+        };
+        match (age, cached()) {
+            (Some(age), Ok(list)) if age < OFFLINE_GRACE => {
+                log::warn!("Using the cached revocation list: {error}");
+                Ok(list)
+            }
+            (Some(age), Ok(_)) => Err(format!(
+                "the revocation list is {} days old and cannot be updated: {error}",
+                age.as_secs() / (24 * 60 * 60)
+            )),
+            _ => Err(format!("the revocation list cannot be fetched: {error}")),
+        }
+    }
+}
+
+/// The keys from the one `signature` was made with up to a root key of
+/// `trusted`, following the certificates of the signature
+fn follow_chain(
+    signature: &PluginSignature,
+    bytes: &[u8],
+    trusted: &[TrustedKey],
+) -> Result<Vec<(String, String)>, String> {
+    let chain = &signature.chain;
+    let mut certificate = chain
+        .iter()
+        .find(|certificate| certificate.key_id == signature.key_id)
+        .ok_or_else(|| format!("no certificate for {} in the chain", signature.key_id))?;
+    let result = signature.check(bytes, &certificate.public_key);
+    if !result.valid {
+        return Err(result.error.unwrap_or_default());
+    }
+    let mut path = Vec::new();
+    for _ in 0..MAX_CHAIN {
+        if certificate
+            .expires_at
+            .is_some_and(|expires_at| Utc::now() > expires_at)
+        {
+            return Err(format!(
+                "the certificate of {} has expired",
+                certificate.key_id
+            ));
+        }
+        path.push((certificate.key_id.clone(), certificate.public_key.clone()));
+        if let Some(root) = trusted
+            .iter()
+            .find(|key| key.level == TrustLevel::Root && certificate.check(&key.key))
+        {
+            path.push((root.id.clone(), root.key.clone()));
+            return Ok(path);
+        }
+        certificate = chain
+            .iter()
+            .find(|issuer| {
+                issuer.key_id == certificate.issuer && certificate.check(&issuer.public_key)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "{} is not certified by a trusted root key",
+                    certificate.key_id
+                )
+            })?;
+    }
+    Err(format!(
+        "the chain of {} is longer than {MAX_CHAIN} certificates",
+        signature.key_id
+    ))
+}
+
+/// Parse the revocation list `bytes` if `signature` is by one of `roots`
+fn verify_list(bytes: &[u8], signature: &str, roots: &[&str]) -> Result<RevocationList> {
+    let signature = BASE64
+        .decode(signature.trim())
+        .context("invalid revocation list signature")?;
+    let signed = roots.iter().any(|root| {
+        Ed25519PublicKey::from_base64(root)
+            .and_then(|key| key.verify(bytes, &signature))
+            .is_ok()
+    });
+    if !signed {
+        bail!("the revocation list is not signed by a root key");
+    }
+    serde_json::from_slice(bytes).context("invalid revocation list")
+}
+
+/// Fetch the revocation list at `url` and its signature, verified
+#[cfg(feature = "remote-plugins")]
+fn fetch_list(url: &str, roots: &[&str]) -> Result<(Vec<u8>, String, RevocationList)> {
+    let bytes = fetch_url(url, &mut |_, _| {})?.ok_or_else(|| anyhow!("{url}: not found"))?;
+    let signature_url = format!("{url}.sig");
+    let signature = fetch_url(&signature_url, &mut |_, _| {})?
+        .ok_or_else(|| anyhow!("{signature_url}: not found"))?;
+    let signature = String::from_utf8(signature).context("invalid revocation list signature")?;
+    let list = verify_list(&bytes, &signature, roots)?;
+    Ok((bytes, signature, list))
+}
+
+#[cfg(not(feature = "remote-plugins"))]
+fn fetch_list(url: &str, _roots: &[&str]) -> Result<(Vec<u8>, String, RevocationList)> {
+    bail!("cannot fetch {url}: built without remote plugin support")
+}
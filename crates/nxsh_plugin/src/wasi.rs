@@ -0,0 +1,971 @@
+//! WASI preview 1 host functions for plugins
+//!
+//! Serves the `wasi_snapshot_preview1` imports of a command built for
+//! `wasm32-wasip1` from a [`WasiCtx`] holding one run's arguments,
+//! environment, standard streams and preopened directories. Standard output
+//! and error are captured rather than written to the terminal, so the shell
+//! can route them like any other command's.
+//!
+//! What a plugin can reach is fixed when its context is built. Files are
+//! only reachable below a preopened directory: absolute paths, `..` above
+//! the preopen and symlinks leading out of it are refused, and a read-only
+//! preopen refuses anything that would write. Sockets are refused unless the
+//! context allows network access. Preview 1 imports not implemented here are
+//! linked to stubs returning `ENOSYS`, so a plugin fails at the call rather
+//! than at load time.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use wasmi::{Caller, Error, Extern, ExternType, Linker, Module, Val};
+
+/// The import module of WASI preview 1
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// WASI error numbers returned to plugins
+pub mod errno {
+    pub const SUCCESS: u16 = 0;
+    pub const ACCES: u16 = 2;
+    pub const BADF: u16 = 8;
+    pub const EXIST: u16 = 20;
+    pub const FAULT: u16 = 21;
+    pub const INVAL: u16 = 28;
+    pub const IO: u16 = 29;
+    pub const ISDIR: u16 = 31;
+    pub const NOENT: u16 = 44;
+    pub const NOSYS: u16 = 52;
+    pub const NOTDIR: u16 = 54;
+    pub const NOTEMPTY: u16 = 55;
+    pub const SPIPE: u16 = 70;
+    pub const NOTCAPABLE: u16 = 76;
+}
+
+use errno::*;
+
+type Errno = u16;
+
+const FILETYPE_UNKNOWN: u8 = 0;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+const FILETYPE_SYMBOLIC_LINK: u8 = 7;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+const RIGHTS_ALL: u64 = (1 << 29) - 1;
+
+const OFLAGS_CREAT: i32 = 1;
+const OFLAGS_DIRECTORY: i32 = 2;
+const OFLAGS_EXCL: i32 = 4;
+const OFLAGS_TRUNC: i32 = 8;
+const FDFLAGS_APPEND: i32 = 1;
+const LOOKUP_SYMLINK_FOLLOW: i32 = 1;
+
+/// The preview 1 functions [`add_to_linker`] defines
+pub(crate) const IMPLEMENTED: &[&str] = &[
+    "args_get",
+    "args_sizes_get",
+    "environ_get",
+    "environ_sizes_get",
+    "clock_res_get",
+    "clock_time_get",
+    "fd_close",
+    "fd_fdstat_get",
+    "fd_filestat_get",
+    "fd_prestat_get",
+    "fd_prestat_dir_name",
+    "fd_read",
+    "fd_readdir",
+    "fd_seek",
+    "fd_tell",
+    "fd_write",
+    "path_create_directory",
+    "path_filestat_get",
+    "path_open",
+    "path_remove_directory",
+    "path_rename",
+    "path_unlink_file",
+    "proc_exit",
+    "random_get",
+    "sched_yield",
+    "sock_accept",
+    "sock_recv",
+    "sock_send",
+    "sock_shutdown",
+];
+
+/// One entry of a plugin's descriptor table
+enum Descriptor {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// A directory below `root`, the preopen it was reached from
+    Dir {
+        path: PathBuf,
+        root: PathBuf,
+        writable: bool,
+        /// The name a preopened directory is announced under
+        preopen: Option<String>,
+    },
+    File {
+        file: File,
+        writable: bool,
+    },
+}
+
+/// The state one plugin run sees through WASI
+pub struct WasiCtx {
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    stdin: Box<dyn Read + Send + Sync>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    descriptors: BTreeMap<u32, Descriptor>,
+    network: bool,
+    started: Instant,
+}
+
+impl std::fmt::Debug for WasiCtx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasiCtx")
+            .field("args", &self.args)
+            .field("env_count", &self.env.len())
+            .field("descriptor_count", &self.descriptors.len())
+            .field("network", &self.network)
+            .finish()
+    }
+}
+
+impl WasiCtx {
+    /// A context with the given arguments, the first being the command
+    /// name, no environment, no preopens, no network and the process's
+    /// standard input
+    pub fn new(args: Vec<String>) -> Self {
+        let descriptors = BTreeMap::from([
+            (0, Descriptor::Stdin),
+            (1, Descriptor::Stdout),
+            (2, Descriptor::Stderr),
+        ]);
+        Self {
+            args,
+            env: Vec::new(),
+            stdin: Box::new(io::stdin()),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            descriptors,
+            network: false,
+            started: Instant::now(),
+        }
+    }
+
+    /// Expose these environment variables
+    pub fn set_env(&mut self, env: Vec<(String, String)>) {
+        self.env = env;
+    }
+
+    /// Read standard input from `stdin` instead of the process's
+    pub fn set_stdin(&mut self, stdin: Box<dyn Read + Send + Sync>) {
+        self.stdin = stdin;
+    }
+
+    /// Let the plugin use sockets it is given
+    pub fn allow_network(&mut self, allow: bool) {
+        self.network = allow;
+    }
+
+    /// Preopen the directory `host` under the name `guest`
+    pub fn preopen(&mut self, guest: &str, host: &Path, writable: bool) -> io::Result<()> {
+        let root = host.canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", host.display()),
+            ));
+        }
+        self.insert(Descriptor::Dir {
+            path: root.clone(),
+            root,
+            writable,
+            preopen: Some(guest.to_string()),
+        });
+        Ok(())
+    }
+
+    /// Take what the plugin wrote to standard output and standard error
+    pub fn take_output(&mut self) -> (Vec<u8>, Vec<u8>) {
+        (
+            std::mem::take(&mut self.stdout),
+            std::mem::take(&mut self.stderr),
+        )
+    }
+
+    fn insert(&mut self, descriptor: Descriptor) -> u32 {
+        let fd = (0..)
+            .find(|fd| !self.descriptors.contains_key(fd))
+            .unwrap_or(u32::MAX);
+        self.descriptors.insert(fd, descriptor);
+        fd
+    }
+
+    fn descriptor(&mut self, fd: i32) -> Result<&mut Descriptor, Errno> {
+        self.descriptors.get_mut(&(fd as u32)).ok_or(BADF)
+    }
+
+    /// The directory `fd` names, as its path, preopen root and writability
+    fn dir(&mut self, fd: i32) -> Result<(PathBuf, PathBuf, bool), Errno> {
+        match self.descriptor(fd)? {
+            Descriptor::Dir {
+                path,
+                root,
+                writable,
+                ..
+            } => Ok((path.clone(), root.clone(), *writable)),
+            _ => Err(NOTDIR),
+        }
+    }
+
+    /// `path` below directory `fd`, checked to stay inside its preopen
+    fn resolve(&mut self, fd: i32, path: &str, write: bool) -> Result<PathBuf, Errno> {
+        let (base, root, writable) = self.dir(fd)?;
+        if write && !writable {
+            return Err(NOTCAPABLE);
+        }
+        resolve(&root, &base, path)
+    }
+}
+
+/// Join `path` to `base` without leaving `root`
+fn resolve(root: &Path, base: &Path, path: &str) -> Result<PathBuf, Errno> {
+    let mut resolved = base.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if resolved == root {
+                    return Err(NOTCAPABLE);
+                }
+                resolved.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(NOTCAPABLE),
+        }
+    }
+    // A symlink on the way may still lead out; see where the deepest part
+    // that exists really is
+    let mut existing = resolved.as_path();
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            if !real.starts_with(root) {
+                return Err(NOTCAPABLE);
+            }
+            break;
+        }
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => break,
+        }
+    }
+    Ok(resolved)
+}
+
+fn from_io(error: &io::Error) -> Errno {
+    match error.kind() {
+        io::ErrorKind::NotFound => NOENT,
+        io::ErrorKind::PermissionDenied => ACCES,
+        io::ErrorKind::AlreadyExists => EXIST,
+        io::ErrorKind::InvalidInput => INVAL,
+        io::ErrorKind::NotADirectory => NOTDIR,
+        io::ErrorKind::IsADirectory => ISDIR,
+        io::ErrorKind::DirectoryNotEmpty => NOTEMPTY,
+        _ => IO,
+    }
+}
+
+/// A plugin's linear memory, with bounds-checked access
+struct Memory<'a>(&'a mut [u8]);
+
+impl Memory<'_> {
+    fn range(&self, ptr: u32, len: usize) -> Result<std::ops::Range<usize>, Errno> {
+        let start = ptr as usize;
+        let end = start.checked_add(len).ok_or(FAULT)?;
+        if end > self.0.len() {
+            return Err(FAULT);
+        }
+        Ok(start..end)
+    }
+
+    fn bytes(&self, ptr: u32, len: u32) -> Result<&[u8], Errno> {
+        let range = self.range(ptr, len as usize)?;
+        Ok(&self.0[range])
+    }
+
+    fn bytes_mut(&mut self, ptr: u32, len: u32) -> Result<&mut [u8], Errno> {
+        let range = self.range(ptr, len as usize)?;
+        Ok(&mut self.0[range])
+    }
+
+    fn str(&self, ptr: u32, len: u32) -> Result<&str, Errno> {
+        std::str::from_utf8(self.bytes(ptr, len)?).map_err(|_| INVAL)
+    }
+
+    fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), Errno> {
+        let range = self.range(ptr, data.len())?;
+        self.0[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn read_u32(&self, ptr: u32) -> Result<u32, Errno> {
+        let bytes = self.bytes(ptr, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn write_u32(&mut self, ptr: u32, value: u32) -> Result<(), Errno> {
+        self.write(ptr, &value.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, ptr: u32, value: u64) -> Result<(), Errno> {
+        self.write(ptr, &value.to_le_bytes())
+    }
+
+    /// The (pointer, length) pairs of an iovec array
+    fn iovecs(&self, iovs: u32, count: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        (0..count)
+            .map(|i| {
+                let at = offset(iovs, i as usize * 8)?;
+                Ok((self.read_u32(at)?, self.read_u32(offset(at, 4)?)?))
+            })
+            .collect()
+    }
+
+    /// Write `items` as NUL-terminated strings into `buf`, with a pointer
+    /// to each in the array at `ptrs`
+    fn write_strings(
+        &mut self,
+        ptrs: u32,
+        buf: u32,
+        items: impl Iterator<Item = Vec<u8>>,
+    ) -> Result<(), Errno> {
+        let mut at = buf;
+        for (i, mut item) in items.enumerate() {
+            self.write_u32(offset(ptrs, i * 4)?, at)?;
+            item.push(0);
+            self.write(at, &item)?;
+            at = offset(at, item.len())?;
+        }
+        Ok(())
+    }
+}
+
+fn offset(ptr: u32, by: usize) -> Result<u32, Errno> {
+    u32::try_from(by)
+        .ok()
+        .and_then(|by| ptr.checked_add(by))
+        .ok_or(FAULT)
+}
+
+/// Run `body` with the calling plugin's memory and context, returning its
+/// error number
+fn call<T>(
+    caller: &mut Caller<'_, T>,
+    get: fn(&mut T) -> &mut WasiCtx,
+    body: impl FnOnce(&mut Memory, &mut WasiCtx) -> Result<(), Errno>,
+) -> Result<i32, Error> {
+    let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+        return Ok(i32::from(NOSYS));
+    };
+    let (data, state) = memory.data_and_store_mut(caller);
+    let result = body(&mut Memory(data), get(state));
+    Ok(i32::from(result.err().unwrap_or(SUCCESS)))
+}
+
+/// Refuse socket calls, with `ENOTCAPABLE` when network access is not
+/// allowed. Preview 1 cannot open sockets and none are ever handed over, so
+/// even an allowed plugin has no socket to use.
+fn socket<T>(caller: &mut Caller<'_, T>, get: fn(&mut T) -> &mut WasiCtx) -> i32 {
+    i32::from(if get(caller.data_mut()).network {
+        BADF
+    } else {
+        NOTCAPABLE
+    })
+}
+
+fn timestamp(time: io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn filetype(metadata: &Metadata) -> u8 {
+    let kind = metadata.file_type();
+    if kind.is_dir() {
+        FILETYPE_DIRECTORY
+    } else if kind.is_file() {
+        FILETYPE_REGULAR_FILE
+    } else if kind.is_symlink() {
+        FILETYPE_SYMBOLIC_LINK
+    } else {
+        FILETYPE_UNKNOWN
+    }
+}
+
+/// Write a `filestat` record: device, inode, type, links, size and times
+fn write_filestat(memory: &mut Memory, ptr: u32, metadata: Option<&Metadata>) -> Result<(), Errno> {
+    let mut record = [0u8; 64];
+    let (kind, size, atime, mtime) = match metadata {
+        Some(metadata) => (
+            filetype(metadata),
+            metadata.len(),
+            timestamp(metadata.accessed()),
+            timestamp(metadata.modified()),
+        ),
+        None => (FILETYPE_CHARACTER_DEVICE, 0, 0, 0),
+    };
+    record[16] = kind;
+    record[24..32].copy_from_slice(&1u64.to_le_bytes());
+    record[32..40].copy_from_slice(&size.to_le_bytes());
+    record[40..48].copy_from_slice(&atime.to_le_bytes());
+    record[48..56].copy_from_slice(&mtime.to_le_bytes());
+    record[56..64].copy_from_slice(&mtime.to_le_bytes());
+    memory.write(ptr, &record)
+}
+
+/// Define the WASI preview 1 functions in `linker`, reaching each run's
+/// context through `get`
+pub fn add_to_linker<T: 'static>(
+    linker: &mut Linker<T>,
+    get: fn(&mut T) -> &mut WasiCtx,
+) -> Result<()> {
+    linker.func_wrap(
+        MODULE,
+        "args_get",
+        move |mut caller: Caller<'_, T>, argv: i32, buf: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let args = ctx.args.iter().map(|arg| arg.clone().into_bytes());
+                memory.write_strings(argv as u32, buf as u32, args)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "args_sizes_get",
+        move |mut caller: Caller<'_, T>, count: i32, size: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let total: usize = ctx.args.iter().map(|arg| arg.len() + 1).sum();
+                memory.write_u32(count as u32, ctx.args.len() as u32)?;
+                memory.write_u32(size as u32, total as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "environ_get",
+        move |mut caller: Caller<'_, T>, environ: i32, buf: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let vars = ctx
+                    .env
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}").into_bytes());
+                memory.write_strings(environ as u32, buf as u32, vars)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "environ_sizes_get",
+        move |mut caller: Caller<'_, T>, count: i32, size: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let total: usize = ctx
+                    .env
+                    .iter()
+                    .map(|(name, value)| name.len() + value.len() + 2)
+                    .sum();
+                memory.write_u32(count as u32, ctx.env.len() as u32)?;
+                memory.write_u32(size as u32, total as u32)
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        MODULE,
+        "clock_res_get",
+        move |mut caller: Caller<'_, T>, id: i32, resolution: i32| {
+            call(&mut caller, get, |memory, _| match id {
+                0..=3 => memory.write_u64(resolution as u32, 1_000),
+                _ => Err(INVAL),
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "clock_time_get",
+        move |mut caller: Caller<'_, T>, id: i32, _precision: i64, time: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let now = match id {
+                    0 => timestamp(Ok(SystemTime::now())),
+                    // Monotonic and CPU time both count from the start of the run
+                    1..=3 => ctx.started.elapsed().as_nanos() as u64,
+                    _ => return Err(INVAL),
+                };
+                memory.write_u64(time as u32, now)
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        MODULE,
+        "fd_close",
+        move |mut caller: Caller<'_, T>, fd: i32| {
+            call(&mut caller, get, |_, ctx| {
+                ctx.descriptors.remove(&(fd as u32)).map(drop).ok_or(BADF)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_fdstat_get",
+        move |mut caller: Caller<'_, T>, fd: i32, stat: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let kind = match ctx.descriptor(fd)? {
+                    Descriptor::Stdin | Descriptor::Stdout | Descriptor::Stderr => {
+                        FILETYPE_CHARACTER_DEVICE
+                    }
+                    Descriptor::Dir { .. } => FILETYPE_DIRECTORY,
+                    Descriptor::File { .. } => FILETYPE_REGULAR_FILE,
+                };
+                let mut record = [0u8; 24];
+                record[0] = kind;
+                record[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+                record[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+                memory.write(stat as u32, &record)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_filestat_get",
+        move |mut caller: Caller<'_, T>, fd: i32, stat: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let metadata = match ctx.descriptor(fd)? {
+                    Descriptor::Stdin | Descriptor::Stdout | Descriptor::Stderr => None,
+                    Descriptor::Dir { path, .. } => {
+                        Some(fs::metadata(path).map_err(|e| from_io(&e))?)
+                    }
+                    Descriptor::File { file, .. } => {
+                        Some(file.metadata().map_err(|e| from_io(&e))?)
+                    }
+                };
+                write_filestat(memory, stat as u32, metadata.as_ref())
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_prestat_get",
+        move |mut caller: Caller<'_, T>, fd: i32, prestat: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                match ctx.descriptor(fd)? {
+                    Descriptor::Dir {
+                        preopen: Some(name),
+                        ..
+                    } => {
+                        let mut record = [0u8; 8];
+                        record[4..8].copy_from_slice(&(name.len() as u32).to_le_bytes());
+                        memory.write(prestat as u32, &record)
+                    }
+                    _ => Err(BADF),
+                }
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_prestat_dir_name",
+        move |mut caller: Caller<'_, T>, fd: i32, path: i32, len: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                match ctx.descriptor(fd)? {
+                    Descriptor::Dir {
+                        preopen: Some(name),
+                        ..
+                    } => {
+                        let name = name.as_bytes();
+                        let len = (len as u32 as usize).min(name.len());
+                        memory.write(path as u32, &name[..len])
+                    }
+                    _ => Err(BADF),
+                }
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_read",
+        move |mut caller: Caller<'_, T>, fd: i32, iovs: i32, count: i32, nread: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let iovecs = memory.iovecs(iovs as u32, count as u32)?;
+                let WasiCtx {
+                    stdin, descriptors, ..
+                } = ctx;
+                let reader: &mut dyn Read = match descriptors.get_mut(&(fd as u32)) {
+                    Some(Descriptor::Stdin) => stdin,
+                    Some(Descriptor::File { file, .. }) => file,
+                    Some(Descriptor::Dir { .. }) => return Err(ISDIR),
+                    Some(_) | None => return Err(BADF),
+                };
+                let mut total = 0u32;
+                for (ptr, len) in iovecs {
+                    let read = reader
+                        .read(memory.bytes_mut(ptr, len)?)
+                        .map_err(|e| from_io(&e))?;
+                    total += read as u32;
+                    if (read as u32) < len {
+                        break;
+                    }
+                }
+                memory.write_u32(nread as u32, total)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_readdir",
+        move |mut caller: Caller<'_, T>, fd: i32, buf: i32, len: i32, cookie: i64, used: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let (path, _, _) = ctx.dir(fd)?;
+                let mut entries = fs::read_dir(&path)
+                    .map_err(|e| from_io(&e))?
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| {
+                        let kind = entry
+                            .metadata()
+                            .map(|metadata| filetype(&metadata))
+                            .unwrap_or(FILETYPE_UNKNOWN);
+                        (entry.file_name().to_string_lossy().into_owned(), kind)
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort();
+                // Entries from the cookie on, each a 24-byte dirent and its
+                // name, cut off where the buffer ends
+                let capacity = len as u32 as usize;
+                let mut out = Vec::new();
+                for (index, (name, kind)) in entries.iter().enumerate().skip(cookie as usize) {
+                    if out.len() >= capacity {
+                        break;
+                    }
+                    out.extend_from_slice(&(index as u64 + 1).to_le_bytes());
+                    out.extend_from_slice(&0u64.to_le_bytes());
+                    out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                    out.extend_from_slice(&[*kind, 0, 0, 0]);
+                    out.extend_from_slice(name.as_bytes());
+                }
+                out.truncate(capacity);
+                memory.write(buf as u32, &out)?;
+                memory.write_u32(used as u32, out.len() as u32)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_seek",
+        move |mut caller: Caller<'_, T>, fd: i32, delta: i64, whence: i32, position: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let Descriptor::File { file, .. } = ctx.descriptor(fd)? else {
+                    return Err(SPIPE);
+                };
+                let from = match whence {
+                    0 => SeekFrom::Start(u64::try_from(delta).map_err(|_| INVAL)?),
+                    1 => SeekFrom::Current(delta),
+                    2 => SeekFrom::End(delta),
+                    _ => return Err(INVAL),
+                };
+                let now = file.seek(from).map_err(|e| from_io(&e))?;
+                memory.write_u64(position as u32, now)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_tell",
+        move |mut caller: Caller<'_, T>, fd: i32, position: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let Descriptor::File { file, .. } = ctx.descriptor(fd)? else {
+                    return Err(SPIPE);
+                };
+                let now = file.stream_position().map_err(|e| from_io(&e))?;
+                memory.write_u64(position as u32, now)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "fd_write",
+        move |mut caller: Caller<'_, T>, fd: i32, iovs: i32, count: i32, nwritten: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let mut data = Vec::new();
+                for (ptr, len) in memory.iovecs(iovs as u32, count as u32)? {
+                    data.extend_from_slice(memory.bytes(ptr, len)?);
+                }
+                let WasiCtx {
+                    stdout,
+                    stderr,
+                    descriptors,
+                    ..
+                } = ctx;
+                match descriptors.get_mut(&(fd as u32)) {
+                    Some(Descriptor::Stdout) => stdout.extend_from_slice(&data),
+                    Some(Descriptor::Stderr) => stderr.extend_from_slice(&data),
+                    Some(Descriptor::File {
+                        file,
+                        writable: true,
+                    }) => file.write_all(&data).map_err(|e| from_io(&e))?,
+                    Some(Descriptor::File { .. }) => return Err(NOTCAPABLE),
+                    Some(Descriptor::Dir { .. }) => return Err(ISDIR),
+                    Some(Descriptor::Stdin) | None => return Err(BADF),
+                }
+                memory.write_u32(nwritten as u32, data.len() as u32)
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        MODULE,
+        "path_create_directory",
+        move |mut caller: Caller<'_, T>, fd: i32, path: i32, len: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let target = ctx.resolve(fd, memory.str(path as u32, len as u32)?, true)?;
+                fs::create_dir(target).map_err(|e| from_io(&e))
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_filestat_get",
+        move |mut caller: Caller<'_, T>, fd: i32, flags: i32, path: i32, len: i32, stat: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let target = ctx.resolve(fd, memory.str(path as u32, len as u32)?, false)?;
+                let metadata = if flags & LOOKUP_SYMLINK_FOLLOW != 0 {
+                    fs::metadata(target)
+                } else {
+                    fs::symlink_metadata(target)
+                };
+                let metadata = metadata.map_err(|e| from_io(&e))?;
+                write_filestat(memory, stat as u32, Some(&metadata))
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_open",
+        move |mut caller: Caller<'_, T>,
+              fd: i32,
+              _lookup: i32,
+              path: i32,
+              len: i32,
+              oflags: i32,
+              rights: i64,
+              _inheriting: i64,
+              fdflags: i32,
+              opened: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let create = oflags & OFLAGS_CREAT != 0;
+                let truncate = oflags & OFLAGS_TRUNC != 0;
+                let append = fdflags & FDFLAGS_APPEND != 0;
+                let write = rights as u64 & RIGHTS_FD_WRITE != 0 || create || truncate || append;
+                let name = memory.str(path as u32, len as u32)?;
+                let target = ctx.resolve(fd, name, write)?;
+                let (_, root, writable) = ctx.dir(fd)?;
+
+                let descriptor = if oflags & OFLAGS_DIRECTORY != 0 || (!write && target.is_dir()) {
+                    if !target.is_dir() {
+                        return Err(if target.exists() { NOTDIR } else { NOENT });
+                    }
+                    Descriptor::Dir {
+                        path: target,
+                        root,
+                        writable,
+                        preopen: None,
+                    }
+                } else {
+                    let file = OpenOptions::new()
+                        .read(!write || rights as u64 & RIGHTS_FD_READ != 0)
+                        .write(write && !append)
+                        .append(append)
+                        .create(create && oflags & OFLAGS_EXCL == 0)
+                        .create_new(create && oflags & OFLAGS_EXCL != 0)
+                        .truncate(truncate)
+                        .open(&target)
+                        .map_err(|e| from_io(&e))?;
+                    Descriptor::File {
+                        file,
+                        writable: write,
+                    }
+                };
+                let new = ctx.insert(descriptor);
+                memory.write_u32(opened as u32, new)
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_remove_directory",
+        move |mut caller: Caller<'_, T>, fd: i32, path: i32, len: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let target = ctx.resolve(fd, memory.str(path as u32, len as u32)?, true)?;
+                fs::remove_dir(target).map_err(|e| from_io(&e))
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_rename",
+        move |mut caller: Caller<'_, T>,
+              fd: i32,
+              old: i32,
+              old_len: i32,
+              new_fd: i32,
+              new: i32,
+              new_len: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let from = ctx.resolve(fd, memory.str(old as u32, old_len as u32)?, true)?;
+                let to = ctx.resolve(new_fd, memory.str(new as u32, new_len as u32)?, true)?;
+                fs::rename(from, to).map_err(|e| from_io(&e))
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "path_unlink_file",
+        move |mut caller: Caller<'_, T>, fd: i32, path: i32, len: i32| {
+            call(&mut caller, get, |memory, ctx| {
+                let target = ctx.resolve(fd, memory.str(path as u32, len as u32)?, true)?;
+                fs::remove_file(target).map_err(|e| from_io(&e))
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        MODULE,
+        "proc_exit",
+        |_: Caller<'_, T>, status: i32| -> Result<(), Error> { Err(Error::i32_exit(status)) },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "random_get",
+        move |mut caller: Caller<'_, T>, buf: i32, len: i32| {
+            call(&mut caller, get, |memory, _| {
+                getrandom::getrandom(memory.bytes_mut(buf as u32, len as u32)?).map_err(|_| IO)
+            })
+        },
+    )?;
+    linker.func_wrap(MODULE, "sched_yield", |_: Caller<'_, T>| -> i32 {
+        i32::from(SUCCESS)
+    })?;
+
+    linker.func_wrap(
+        MODULE,
+        "sock_accept",
+        move |mut caller: Caller<'_, T>, _fd: i32, _flags: i32, _accepted: i32| {
+            socket(&mut caller, get)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "sock_recv",
+        move |mut caller: Caller<'_, T>,
+              _fd: i32,
+              _iovs: i32,
+              _count: i32,
+              _flags: i32,
+              _received: i32,
+              _out_flags: i32| { socket(&mut caller, get) },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "sock_send",
+        move |mut caller: Caller<'_, T>,
+              _fd: i32,
+              _iovs: i32,
+              _count: i32,
+              _flags: i32,
+              _sent: i32| { socket(&mut caller, get) },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "sock_shutdown",
+        move |mut caller: Caller<'_, T>, _fd: i32, _how: i32| socket(&mut caller, get),
+    )?;
+    Ok(())
+}
+
+/// Link the preview 1 imports of `module` that [`add_to_linker`] does not
+/// define to stubs returning `ENOSYS`. Imports from any other module are an
+/// error, as a plugin cannot be given them.
+pub fn link_stubs<T: 'static>(linker: &mut Linker<T>, module: &Module) -> Result<()> {
+    for import in module.imports() {
+        if import.module() != MODULE {
+            return Err(anyhow!(
+                "imports {}::{}, which plugins cannot use",
+                import.module(),
+                import.name()
+            ));
+        }
+        let ExternType::Func(ty) = import.ty() else {
+            return Err(anyhow!(
+                "imports {MODULE}::{}, which is not a function",
+                import.name()
+            ));
+        };
+        if IMPLEMENTED.contains(&import.name()) {
+            continue;
+        }
+        linker.func_new(MODULE, import.name(), ty.clone(), |_, _, results| {
+            if let Some(result) = results.first_mut() {
+                *result = Val::I32(i32::from(NOSYS));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_stay_inside_the_preopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+
+        assert_eq!(
+            resolve(&root, &root, "sub/./a.txt"),
+            Ok(root.join("sub/a.txt"))
+        );
+        assert_eq!(
+            resolve(&root, &root.join("sub"), "../b"),
+            Ok(root.join("b"))
+        );
+        assert_eq!(resolve(&root, &root, "../etc/passwd"), Err(NOTCAPABLE));
+        assert_eq!(resolve(&root, &root, "/etc/passwd"), Err(NOTCAPABLE));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/", root.join("out")).unwrap();
+            assert_eq!(resolve(&root, &root, "out/etc"), Err(NOTCAPABLE));
+        }
+    }
+
+    #[test]
+    fn memory_access_is_bounds_checked() {
+        let mut bytes = [0u8; 16];
+        let mut memory = Memory(&mut bytes);
+        memory.write_u32(12, 7).unwrap();
+        assert_eq!(memory.read_u32(12), Ok(7));
+        assert_eq!(memory.write_u32(13, 7), Err(FAULT));
+        assert_eq!(memory.bytes(u32::MAX, 2), Err(FAULT));
+    }
+}
@@ -0,0 +1,213 @@
+#![cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+
+use anyhow::Result;
+use nxsh_plugin::consent::{
+    CapabilityConsent, ConsentPrompt, ConsentRequest, Decision, PermissionGrants, PluginGrants,
+};
+use nxsh_plugin::runtime::MANIFEST_SECTION;
+use nxsh_plugin::{PluginEvent, PluginEventHandler, PluginManager};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// A plugin whose commands return what WASI reports about fd 3 and the
+/// environment, asking to read files and the environment
+const PROBE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_prestat_get" (func $prestat (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_sizes_get" (func $sizes (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "probe_preopen") (result i32)
+    (call $prestat (i32.const 3) (i32.const 0)))
+  (func (export "probe_env") (result i32)
+    (drop (call $sizes (i32.const 0) (i32.const 4)))
+    (i32.load (i32.const 0))))
+"#;
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write probe `version` to `dir`
+fn write_probe(dir: &Path, version: &str) -> PathBuf {
+    let manifest = format!(
+        r#"{{"name": "probe", "version": "{version}", "description": "Looks around",
+            "capabilities": ["file_read", "env_read"],
+            "commands": ["probe_preopen", "probe_env"]}}"#
+    );
+    let mut wasm = wat::parse_str(PROBE).unwrap();
+    let mut section = leb128(MANIFEST_SECTION.len());
+    section.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    section.extend_from_slice(manifest.as_bytes());
+    wasm.push(0);
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    let path = dir.join("probe.wasm");
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+/// Answers with `answers`, or not at all without them, and records what
+/// it was asked
+struct Scripted {
+    answers: Option<Vec<Decision>>,
+    asked: Arc<Mutex<Vec<String>>>,
+}
+
+impl ConsentPrompt for Scripted {
+    fn ask(&self, request: &ConsentRequest<'_>) -> Option<Vec<Decision>> {
+        assert_eq!(request.description, "Looks around");
+        self.asked.lock().unwrap().push(format!(
+            "{} {}: {}",
+            request.name,
+            request.version,
+            request.capabilities.join(" ")
+        ));
+        self.answers.clone()
+    }
+}
+
+struct Recorder(Arc<Mutex<Vec<PluginEvent>>>);
+
+impl PluginEventHandler for Recorder {
+    fn handle_event(
+        &self,
+        event: PluginEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.0.lock().unwrap().push(event);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A manager deciding with the grants in `grants` and a prompt giving
+/// `answers`, and the events it emits
+async fn consenting_manager(
+    grants: &Path,
+    answers: Option<Vec<Decision>>,
+    asked: &Arc<Mutex<Vec<String>>>,
+) -> (PluginManager, Arc<Mutex<Vec<PluginEvent>>>) {
+    let mut manager = PluginManager::new();
+    manager.initialize_runtimes().await.unwrap();
+    let prompt = Scripted {
+        answers,
+        asked: asked.clone(),
+    };
+    manager.set_consent(CapabilityConsent::new(
+        PermissionGrants::new(grants),
+        Some(Box::new(prompt)),
+    ));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    manager.add_event_handler(Box::new(Recorder(events.clone())));
+    (manager, events)
+}
+
+/// The capabilities granted and denied in `events`, with why they were denied
+fn decisions(events: &Mutex<Vec<PluginEvent>>) -> Vec<(String, Option<String>)> {
+    events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            PluginEvent::PermissionGranted { capability, .. } => Some((capability.clone(), None)),
+            PluginEvent::PermissionDenied {
+                capability, reason, ..
+            } => Some((capability.clone(), Some(reason.clone()))),
+            _ => None,
+        })
+        .collect()
+}
+
+async fn status(manager: &PluginManager, command: &str, cwd: &Path) -> i32 {
+    let env = HashMap::from([("HOME".to_string(), "/home/u".to_string())]);
+    manager
+        .wasi_runtime()
+        .unwrap()
+        .run_command(command, &[], &env, cwd)
+        .await
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn grants_what_the_user_allows_and_remembers_it_per_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let grants = dir.path().join("plugin-permissions.toml");
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let path = write_probe(dir.path(), "1.0.0");
+
+    let answers = vec![Decision::Granted, Decision::Denied];
+    let (mut manager, events) = consenting_manager(&grants, Some(answers), &asked).await;
+    let id = manager.load_plugin(&path).await.unwrap();
+    assert_eq!(*asked.lock().unwrap(), ["probe 1.0.0: file_read env_read"]);
+    assert_eq!(
+        decisions(&events),
+        [
+            ("file_read".to_string(), None),
+            (
+                "env_read".to_string(),
+                Some("denied by the user".to_string())
+            ),
+        ]
+    );
+    // The working directory is preopened but the environment is empty
+    assert_eq!(status(&manager, "probe_preopen", dir.path()).await, 0);
+    assert_eq!(status(&manager, "probe_env", dir.path()).await, 0);
+    manager.unload_plugin(&id).await.unwrap();
+    assert_eq!(
+        PermissionGrants::new(&grants)
+            .get("probe", "1.0.0")
+            .unwrap(),
+        PluginGrants {
+            granted: vec!["file_read".to_string()],
+            denied: vec!["env_read".to_string()],
+        }
+    );
+
+    // The recorded decisions apply without asking again
+    let (mut manager, events) = consenting_manager(&grants, None, &asked).await;
+    let id = manager.load_plugin(&path).await.unwrap();
+    assert_eq!(asked.lock().unwrap().len(), 1);
+    assert_eq!(
+        decisions(&events),
+        [
+            ("file_read".to_string(), None),
+            ("env_read".to_string(), Some("denied earlier".to_string())),
+        ]
+    );
+    manager.unload_plugin(&id).await.unwrap();
+
+    // A new version is asked about, and without an answer gets nothing
+    let path = write_probe(dir.path(), "1.1.0");
+    let (mut manager, events) = consenting_manager(&grants, None, &asked).await;
+    manager.load_plugin(&path).await.unwrap();
+    assert_eq!(
+        asked.lock().unwrap().last().unwrap(),
+        "probe 1.1.0: file_read env_read"
+    );
+    let unconfirmed = Some("not confirmed by the user".to_string());
+    assert_eq!(
+        decisions(&events),
+        [
+            ("file_read".to_string(), unconfirmed.clone()),
+            ("env_read".to_string(), unconfirmed),
+        ]
+    );
+    // EBADF without file_read
+    assert_eq!(status(&manager, "probe_preopen", dir.path()).await, 8);
+    let recorded = PermissionGrants::new(&grants).list().unwrap();
+    assert_eq!(recorded.keys().collect::<Vec<_>>(), ["probe@1.0.0"]);
+
+    assert_eq!(PermissionGrants::new(&grants).forget("probe").unwrap(), 1);
+    assert!(PermissionGrants::new(&grants).list().unwrap().is_empty());
+}
@@ -0,0 +1,147 @@
+#![cfg(all(feature = "hot-reload", feature = "wasi-runtime"))]
+
+use anyhow::Result;
+use nxsh_plugin::dev::{DevSession, Reloaded};
+use nxsh_plugin::runtime::MANIFEST_SECTION;
+use nxsh_plugin::{PluginEvent, PluginEventHandler, PluginManager};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write greeter `version`, whose `greet` command prints `text`, to `path`
+fn write_greeter(path: &Path, version: &str, text: &str) {
+    let wat = format!(
+        r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{escaped}")
+  (func (export "greet")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const {len}))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))
+"#,
+        escaped = text.replace('\n', "\\n"),
+        len = text.len()
+    );
+    write_plugin(path, &wat, version);
+}
+
+/// Write `wat` as greeter `version` with a `greet` command to `path`
+fn write_plugin(path: &Path, wat: &str, version: &str) {
+    let manifest =
+        format!(r#"{{"name": "greeter", "version": "{version}", "commands": ["greet"]}}"#);
+    let mut wasm = wat::parse_str(wat).unwrap();
+    let mut section = leb128(MANIFEST_SECTION.len());
+    section.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    section.extend_from_slice(manifest.as_bytes());
+    wasm.push(0);
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    std::fs::write(path, wasm).unwrap();
+}
+
+async fn greet(manager: &PluginManager, cwd: &Path) -> Vec<u8> {
+    manager
+        .wasi_runtime()
+        .unwrap()
+        .run_command("greet", &[], &HashMap::new(), cwd)
+        .await
+        .unwrap()
+        .stdout
+}
+
+struct Recorder(Arc<Mutex<Vec<PluginEvent>>>);
+
+impl PluginEventHandler for Recorder {
+    fn handle_event(
+        &self,
+        event: PluginEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.0.lock().unwrap().push(event);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn reloads_in_place_and_keeps_the_old_plugin_when_the_new_one_is_broken() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("greeter.wasm");
+    write_greeter(&path, "1.0.0", "hello\n");
+    let mut manager = PluginManager::new();
+    manager.initialize_runtimes().await.unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    manager.add_event_handler(Box::new(Recorder(events.clone())));
+    let id = manager.load_plugin(&path).await.unwrap();
+
+    write_greeter(&path, "1.1.0", "hello again\n");
+    let id = manager.reload_plugin(&id, &path).await.unwrap();
+    assert_eq!(id, "greeter@1.1.0");
+    assert_eq!(greet(&manager, dir.path()).await, b"hello again\n");
+    assert!(matches!(
+        events.lock().unwrap().last(),
+        Some(PluginEvent::Updated { plugin_id, old_version, new_version })
+            if plugin_id == "greeter@1.1.0" && old_version == "1.0.0" && new_version == "1.1.0"
+    ));
+
+    // A build without the command does not replace the working plugin
+    write_plugin(&path, r#"(module (func (export "wave")))"#, "1.2.0");
+    let error = manager.reload_plugin(&id, &path).await.unwrap_err();
+    assert!(format!("{error:#}").contains("exports no function 'greet'"));
+    assert_eq!(greet(&manager, dir.path()).await, b"hello again\n");
+    assert_eq!(manager.list_plugins(), ["greeter@1.1.0"]);
+}
+
+#[tokio::test]
+async fn dev_session_reloads_when_the_artifact_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("greeter.wasm");
+    write_greeter(&path, "1.0.0", "hello\n");
+    nxsh_plugin::initialize().await.unwrap();
+    let env = HashMap::new();
+    let run = || async {
+        nxsh_plugin::run_command("greet", &[], &env, dir.path())
+            .await
+            .unwrap()
+            .stdout
+    };
+
+    let mut session = DevSession::start(&path).await.unwrap();
+    assert_eq!(session.plugin_id(), "greeter@1.0.0");
+    assert_eq!(session.replaced(), None);
+    assert_eq!(run().await, b"hello\n");
+    assert!(!session.wait_for_change(Duration::from_millis(600)));
+
+    write_greeter(&path, "1.1.0", "hello again\n");
+    assert!(session.wait_for_change(Duration::from_secs(10)));
+    let reloaded = session.reload().await.unwrap();
+    assert_eq!(
+        reloaded,
+        Reloaded {
+            old_id: "greeter@1.0.0".to_string(),
+            old_version: "1.0.0".to_string(),
+            plugin_id: "greeter@1.1.0".to_string(),
+            version: "1.1.0".to_string(),
+        }
+    );
+    assert_eq!(run().await, b"hello again\n");
+
+    session.stop().await.unwrap();
+    assert!(nxsh_plugin::plugin_commands().await.is_empty());
+}
@@ -0,0 +1,170 @@
+#![cfg(all(unix, feature = "native-plugins"))]
+
+use nxsh_plugin::isolation::{read_frame, write_frame, Execution, PluginHost, Request, Response};
+use nxsh_plugin::PluginConfig;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const FAKE_HOST: &str = "NXSH_TEST_FAKE_PLUGIN_HOST";
+
+/// Stands in for `nxsh-plugin-host` when this test binary is run with
+/// `FAKE_HOST` set, with commands that misbehave on demand; otherwise passes
+#[test]
+fn fake_host() {
+    if std::env::var_os(FAKE_HOST).is_none() {
+        return;
+    }
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout();
+    while let Some(request) = read_frame::<Request>(&mut input).unwrap() {
+        let response = match request {
+            Request::Load { path, .. } if path.ends_with("broken.so") => Response::Error {
+                message: "not a plugin".to_string(),
+            },
+            Request::Load { .. } => Response::Loaded,
+            Request::Execute { command, args } => match command.as_str() {
+                "echo" => {
+                    writeln!(output, "{}", args.join(" ")).unwrap();
+                    Response::Done { status: 0 }
+                }
+                "fail" => Response::Done { status: 3 },
+                "crash" => std::process::abort(),
+                "hang" => {
+                    std::thread::sleep(Duration::from_secs(60));
+                    Response::Done { status: 0 }
+                }
+                _ => Response::Error {
+                    message: format!("{command}: no such command"),
+                },
+            },
+        };
+        write_frame(&mut output, &response).unwrap();
+    }
+    std::process::exit(0);
+}
+
+/// A script running this test binary as the fake host
+fn fake_host_program(dir: &Path) -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let script = dir.join("fake-host");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nexec env {FAKE_HOST}=1 '{}' --exact fake_host --nocapture --test-threads=1\n",
+            exe.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+fn host_config(program: PathBuf, timeout_ms: u64, max_restarts: u32) -> PluginConfig {
+    PluginConfig {
+        execution_timeout_ms: timeout_ms,
+        max_restarts,
+        host_program: Some(program),
+        ..PluginConfig::default()
+    }
+}
+
+fn echo(host: &mut PluginHost, text: &str) -> Execution {
+    host.execute("echo", &[text.to_string()]).unwrap()
+}
+
+#[test]
+fn runs_commands_in_the_host_and_restarts_it_after_a_crash() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = host_config(fake_host_program(dir.path()), 10_000, 1);
+    let mut host = PluginHost::start(&config, dir.path().join("hello.so"), "hello").unwrap();
+
+    assert_eq!(
+        echo(&mut host, "hi"),
+        Execution {
+            status: 0,
+            output: b"hi\n".to_vec(),
+        }
+    );
+    assert_eq!(host.execute("fail", &[]).unwrap().status, 3);
+    let error = host.execute("sing", &[]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Plugin execution error: sing: no such command"
+    );
+
+    let pid = host.pid();
+    let error = host.execute("crash", &[]).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("the plugin host for 'hello' crashed running 'crash'"),
+        "{error}"
+    );
+    assert_eq!(host.pid(), None);
+    // The next command gets a new host
+    assert_eq!(echo(&mut host, "again").output, b"again\n");
+    assert_ne!(host.pid(), pid);
+
+    // Until it has been restarted `max_restarts` times
+    host.execute("crash", &[]).unwrap_err();
+    let error = host.execute("echo", &[]).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("stopped 2 times; not starting it again"),
+        "{error}"
+    );
+}
+
+#[test]
+fn kills_hosts_that_run_past_the_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = host_config(fake_host_program(dir.path()), 2_000, 3);
+    let mut host = PluginHost::start(&config, dir.path().join("hello.so"), "hello").unwrap();
+
+    let started = Instant::now();
+    let error = host.execute("hang", &[]).unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(30));
+    assert_eq!(
+        error.to_string(),
+        "Plugin execution error: plugin 'hello' timed out after 2000 ms running 'hang'"
+    );
+    assert_eq!(echo(&mut host, "awake").output, b"awake\n");
+}
+
+#[test]
+fn reports_plugins_the_host_cannot_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = host_config(fake_host_program(dir.path()), 10_000, 3);
+    let error = PluginHost::start(&config, dir.path().join("broken.so"), "broken").unwrap_err();
+    assert_eq!(error.to_string(), "Plugin load error: not a plugin");
+
+    // The real host refuses a library that is not one
+    let empty = dir.path().join("empty.so");
+    std::fs::write(&empty, b"").unwrap();
+    let config = host_config(
+        PathBuf::from(env!("CARGO_BIN_EXE_nxsh-plugin-host")),
+        10_000,
+        3,
+    );
+    let error = PluginHost::start(&config, &empty, "empty").unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("Plugin load error: Failed to load library"),
+        "{error}"
+    );
+
+    let error = PluginHost::start(
+        &PluginConfig {
+            host_program: Some(dir.path().join("missing")),
+            ..config
+        },
+        &empty,
+        "empty",
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("cannot start"), "{error}");
+}
@@ -0,0 +1,158 @@
+#![cfg(all(
+    feature = "plugin-management",
+    feature = "wasi-runtime",
+    feature = "crypto-verification"
+))]
+
+use nxsh_plugin::runtime::MANIFEST_SECTION;
+use nxsh_plugin::signature::{Ed25519PrivateKey, SignatureVerifier};
+use nxsh_plugin::store::{PluginStore, SignatureStatus};
+use nxsh_plugin::PluginManager;
+use std::path::{Path, PathBuf};
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write a plugin named `name` at `version` as `dir/file`
+fn write_plugin(dir: &Path, file: &str, name: &str, version: &str) -> PathBuf {
+    let manifest = format!(r#"{{"name": "{name}", "version": "{version}"}}"#);
+    let mut wasm = wat::parse_str(r#"(module (func (export "_start")))"#).unwrap();
+    let mut section = leb128(MANIFEST_SECTION.len());
+    section.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    section.extend_from_slice(manifest.as_bytes());
+    wasm.push(0);
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    let path = dir.join(file);
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+fn source(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn installs_disables_and_removes_plugins() {
+    let downloads = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let store = PluginStore::new(dir.path());
+    let manager = PluginManager::new();
+
+    let path = write_plugin(downloads.path(), "hello-1.0.0.wasm", "hello", "1.0.0");
+    let plugin = store.install(&manager, source(&path), None).unwrap();
+    assert_eq!(plugin.id(), "hello@1.0.0");
+    assert_eq!(plugin.file, "hello.wasm");
+    assert!(plugin.enabled);
+    assert_eq!(store.list().unwrap(), [plugin.clone()]);
+    assert_eq!(store.signature_status(&plugin), SignatureStatus::Unsigned);
+    let error = store.install(&manager, source(&path), None).unwrap_err();
+    assert!(error.to_string().contains("already installed"));
+
+    write_plugin(dir.path(), "manual.wasm", "manual", "0.1.0");
+    let names = |files: Vec<PathBuf>| -> Vec<String> {
+        files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+    assert_eq!(
+        names(store.startup_files().unwrap()),
+        ["hello.wasm", "manual.wasm"]
+    );
+    assert!(!store.set_enabled("hello", false).unwrap().enabled);
+    assert_eq!(names(store.startup_files().unwrap()), ["manual.wasm"]);
+    assert!(store.set_enabled("hello", true).unwrap().enabled);
+
+    store.remove("hello").unwrap();
+    assert!(store.list().unwrap().is_empty());
+    assert!(!dir.path().join("hello.wasm").exists());
+    assert!(store.remove("hello").is_err());
+}
+
+#[test]
+fn updates_only_to_newer_versions() {
+    let downloads = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let store = PluginStore::new(dir.path());
+    let manager = PluginManager::new();
+
+    let path = write_plugin(downloads.path(), "hello.wasm", "hello", "1.0.0");
+    store.install(&manager, source(&path), None).unwrap();
+    store.set_enabled("hello", false).unwrap();
+
+    let (old, plugin) = store.update(&manager, "hello", None, false).unwrap();
+    assert_eq!((old.as_str(), plugin.version.as_str()), ("1.0.0", "1.0.0"));
+
+    let older = write_plugin(downloads.path(), "old.wasm", "hello", "0.9.0");
+    let error = store
+        .update(&manager, "hello", Some(source(&older)), false)
+        .unwrap_err();
+    assert!(error.to_string().contains("older than the installed 1.0.0"));
+
+    let other = write_plugin(downloads.path(), "other.wasm", "other", "2.0.0");
+    assert!(store
+        .update(&manager, "hello", Some(source(&other)), false)
+        .is_err());
+
+    write_plugin(downloads.path(), "hello.wasm", "hello", "1.1.0");
+    let (old, plugin) = store.update(&manager, "hello", None, false).unwrap();
+    assert_eq!((old.as_str(), plugin.version.as_str()), ("1.0.0", "1.1.0"));
+    assert!(!plugin.enabled);
+    let cached = dir.path().join(".cache/hello/hello-1.0.0.wasm");
+    assert!(cached.exists());
+
+    store.remove("hello").unwrap();
+    assert!(!cached.exists());
+}
+
+#[tokio::test]
+async fn checks_signatures_against_pinned_keys() {
+    let downloads = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let store = PluginStore::new(dir.path());
+    let manager = PluginManager::new();
+
+    let key = Ed25519PrivateKey::from_bytes(&[7; 32]).unwrap();
+    let public = key.public_key().unwrap().to_base64();
+    let path = write_plugin(downloads.path(), "signed.wasm", "signed", "1.0.0");
+    SignatureVerifier::new()
+        .unwrap()
+        .sign_plugin(&path, &key, "test-key".to_string())
+        .await
+        .unwrap();
+
+    let plugin = store
+        .install(&manager, source(&path), Some(&public))
+        .unwrap();
+    assert_eq!(
+        store.signature_status(&plugin),
+        SignatureStatus::Verified("test-key".to_string())
+    );
+    std::fs::write(dir.path().join("signed.wasm"), b"\0asm\x01\0\0\0").unwrap();
+    assert!(matches!(
+        store.signature_status(&plugin),
+        SignatureStatus::Invalid(_)
+    ));
+    store.remove("signed").unwrap();
+    assert!(!dir.path().join("signed.sig").exists());
+    assert!(!dir.path().join("keys/signed.pub").exists());
+
+    // A plugin changed after it was signed is not installed
+    write_plugin(downloads.path(), "signed.wasm", "signed", "1.0.1");
+    let error = store
+        .install(&manager, source(&path), Some(&public))
+        .unwrap_err();
+    assert!(error.to_string().contains("signature of signed is invalid"));
+    assert!(store.list().unwrap().is_empty());
+}
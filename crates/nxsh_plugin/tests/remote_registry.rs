@@ -0,0 +1,159 @@
+#![cfg(feature = "remote-plugins")]
+
+use base64::engine::{general_purpose::STANDARD as BASE64, Engine};
+use nxsh_plugin::remote::{RemotePluginManager, RemoteRepository};
+use nxsh_plugin::signature::Ed25519PrivateKey;
+use nxsh_plugin::PluginEvent;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// Nothing listens on this port, so a repository there cannot be reached
+const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+/// Serve `files` over HTTP on a local port, returning its base URL
+fn serve(files: HashMap<String, Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = Vec::new();
+            let mut byte = [0u8; 1];
+            while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                request.push(byte[0]);
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, body) = match files.get(path) {
+                Some(body) => ("200 OK", body.clone()),
+                None => ("404 Not Found", Vec::new()),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&body);
+        }
+    });
+    url
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn key() -> Ed25519PrivateKey {
+    Ed25519PrivateKey::from_bytes(&[9; 32]).unwrap()
+}
+
+/// A repository with greeter 1.0.0 and 1.1.0, a 2.0.0 for a later shell, a
+/// 1.2.0 for another platform, and `index` signed with `signer`
+fn repository_files(signer: &Ed25519PrivateKey) -> HashMap<String, Vec<u8>> {
+    let index = format!(
+        r#"{{"plugins": {{"greeter": [
+            {{"version": "1.0.0", "url": "greeter-1.0.0.wasm", "sha256": "{}"}},
+            {{"version": "1.1.0", "url": "/greeter-1.1.0.wasm", "sha256": "{}", "size": 10,
+              "min_nexus_version": "0.1.0"}},
+            {{"version": "2.0.0", "url": "greeter-2.0.0.wasm", "sha256": "", "min_nexus_version": "99.0.0"}},
+            {{"version": "1.2.0", "url": "greeter-1.2.0.wasm", "sha256": "", "platforms": ["plan9-mips"]}}
+        ]}}}}"#,
+        sha256(b"greeter v1"),
+        sha256(b"greeter v2"),
+    );
+    let signature = BASE64.encode(signer.sign(index.as_bytes()).unwrap());
+    HashMap::from([
+        ("/index.json".to_string(), index.into_bytes()),
+        ("/index.json.sig".to_string(), signature.into_bytes()),
+        ("/greeter-1.0.0.wasm".to_string(), b"greeter v1".to_vec()),
+        ("/greeter-1.1.0.wasm".to_string(), b"greeter v2".to_vec()),
+    ])
+}
+
+fn repository(base_url: &str, mirrors: Vec<String>) -> RemoteRepository {
+    RemoteRepository {
+        name: "test".to_string(),
+        base_url: base_url.to_string(),
+        public_key: key().public_key().unwrap().to_base64(),
+        priority: 10,
+        enabled: true,
+        mirrors,
+    }
+}
+
+#[test]
+fn resolves_downloads_and_works_offline_from_the_cache() {
+    let url = serve(repository_files(&key()));
+    let cache = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let dest = out.path().join("greeter.wasm");
+
+    let mut manager = RemotePluginManager::new(cache.path()).unwrap();
+    manager.set_offline(false);
+    manager.add_repository(repository(UNREACHABLE, vec![url]));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    manager.on_event(move |event| sink.lock().unwrap().push(event));
+
+    let info = manager.download_plugin("greeter", &dest).unwrap();
+    assert_eq!(info.id, "greeter@1.1.0");
+    assert_eq!(std::fs::read(&dest).unwrap(), b"greeter v2");
+    {
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            PluginEvent::DownloadProgress {
+                downloaded: 10,
+                total: Some(10),
+                ..
+            }
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(PluginEvent::Downloaded { plugin_id, .. }) if plugin_id == "greeter@1.1.0"
+        ));
+    }
+
+    let info = manager.download_plugin("greeter@~1.0", &dest).unwrap();
+    assert_eq!(info.version, "1.0.0");
+    assert!(manager.download_plugin("greeter@>=3", &dest).is_err());
+
+    // The verified index and downloads serve an offline manager
+    let mut offline = RemotePluginManager::new(cache.path()).unwrap();
+    offline.set_offline(true);
+    offline.add_repository(repository(UNREACHABLE, Vec::new()));
+    let info = offline.download_plugin("greeter", &dest).unwrap();
+    assert_eq!(info.version, "1.1.0");
+    assert_eq!(std::fs::read(&dest).unwrap(), b"greeter v2");
+    let available = offline.list_available_plugins().unwrap();
+    assert_eq!(available["test"].len(), 1);
+    assert_eq!(available["test"][0].version, "1.1.0");
+}
+
+#[test]
+fn rejects_unsigned_indexes_and_corrupt_downloads() {
+    let cache = tempfile::tempdir().unwrap();
+    let dest = cache.path().join("greeter.wasm");
+
+    let other = Ed25519PrivateKey::from_bytes(&[3; 32]).unwrap();
+    let url = serve(repository_files(&other));
+    let mut manager = RemotePluginManager::new(cache.path()).unwrap();
+    manager.set_offline(false);
+    manager.add_repository(repository(&url, Vec::new()));
+    let error = manager.download_plugin("greeter", &dest).unwrap_err();
+    assert!(format!("{error:#}").contains("not signed by its key"));
+
+    let mut files = repository_files(&key());
+    files.insert("/greeter-1.1.0.wasm".to_string(), b"tampered!!".to_vec());
+    let url = serve(files);
+    let cache = tempfile::tempdir().unwrap();
+    let mut manager = RemotePluginManager::new(cache.path()).unwrap();
+    manager.set_offline(false);
+    manager.add_repository(repository(&url, Vec::new()));
+    let error = manager.download_plugin("greeter", &dest).unwrap_err();
+    assert!(format!("{error:#}").contains("Checksum mismatch"));
+    assert!(!dest.exists());
+}
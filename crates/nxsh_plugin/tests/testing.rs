@@ -0,0 +1,203 @@
+#![cfg(all(feature = "plugin-management", feature = "wasi-runtime"))]
+
+use nxsh_plugin::runtime::CommandOutput;
+use nxsh_plugin::testing::{
+    assert_golden_output, embed_manifest, render_output, verify, CapabilityRecord, CheckStatus,
+    MockPluginManager,
+};
+use std::path::Path;
+
+/// Greets on standard output and fails with status 3
+const GREETER: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "hello\n")
+  (func (export "greet") (result i32)
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 6))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    (i32.const 0))
+  (func (export "fail") (result i32) (i32.const 3)))
+"#;
+
+fn greeter() -> Vec<u8> {
+    embed_manifest(
+        &wat::parse_str(GREETER).unwrap(),
+        r#"{"name": "greeter", "version": "1.0.0",
+            "capabilities": ["env_read", "network_request"], "commands": ["greet", "fail"]}"#,
+    )
+}
+
+fn write(dir: &Path, file: &str, wasm: &[u8]) -> std::path::PathBuf {
+    let path = dir.join(file);
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn runs_plugins_with_the_capabilities_a_test_grants() {
+    let mut plugins = MockPluginManager::new()
+        .await
+        .unwrap()
+        .deny("network_request");
+    let id = plugins
+        .load_bytes("greeter.wasm", &greeter())
+        .await
+        .unwrap();
+    assert_eq!(id, "greeter@1.0.0");
+
+    let recorder = plugins.recorder();
+    assert_eq!(
+        recorder.requested("greeter"),
+        ["env_read", "network_request"]
+    );
+    assert_eq!(recorder.granted(&id), ["env_read"]);
+    assert_eq!(recorder.denied(&id), ["network_request"]);
+    assert!(recorder.records().contains(&CapabilityRecord::Denied {
+        plugin_id: id.clone(),
+        capability: "network_request".to_string(),
+        reason: "denied by the user".to_string(),
+    }));
+
+    assert_eq!(plugins.commands().await, ["fail", "greet"]);
+    let output = plugins.run("greet", &[]).await.unwrap();
+    assert_eq!(
+        render_output(&output),
+        "status: 0\n--- stdout\nhello\n--- stderr\n"
+    );
+    assert_eq!(plugins.run("fail", &[]).await.unwrap().status, 3);
+
+    plugins.unload(&id).await.unwrap();
+    assert!(plugins.commands().await.is_empty());
+    assert!(plugins.run("greet", &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn denies_what_is_not_granted_when_told_to() {
+    let mut plugins = MockPluginManager::new()
+        .await
+        .unwrap()
+        .grant("env_read")
+        .deny_others();
+    let id = plugins
+        .load_bytes("greeter.wasm", &greeter())
+        .await
+        .unwrap();
+    assert_eq!(plugins.recorder().granted(&id), ["env_read"]);
+    assert_eq!(plugins.recorder().denied(&id), ["network_request"]);
+}
+
+#[test]
+fn compares_output_with_golden_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let golden = dir.path().join("greet.txt");
+    std::fs::write(&golden, "status: 0\n--- stdout\nhello\n--- stderr\n").unwrap();
+    let output = CommandOutput {
+        status: 0,
+        stdout: b"hello\n".to_vec(),
+        stderr: Vec::new(),
+    };
+    assert_golden_output(&golden, &output);
+
+    let changed = CommandOutput {
+        stdout: b"hi".to_vec(),
+        ..output
+    };
+    assert_eq!(
+        render_output(&changed),
+        "status: 0\n--- stdout\nhi\n\\ no newline at end\n--- stderr\n"
+    );
+    let panic = std::panic::catch_unwind(|| assert_golden_output(&golden, &changed)).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains("differs at line 3\nexpected: hello\n  actual: hi\n"),
+        "{message}"
+    );
+}
+
+#[test]
+fn verifies_plugins_the_shell_can_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let report = verify(write(dir.path(), "greeter.wasm", &greeter())).unwrap();
+    assert!(report.passed(), "{report}");
+    assert_eq!(
+        report.to_string(),
+        "ok    module        valid WebAssembly\n\
+         ok    manifest      nxsh_plugin\n\
+         ok    metadata      greeter 1.0.0\n\
+         ok    capabilities  env_read, network_request\n\
+         ok    imports       wasi_snapshot_preview1\n\
+         ok    exports       fail, greet\n\
+         ok    memory        exported\n"
+    );
+
+    // Without a manifest a plugin still loads, as a command named after it
+    let plain = wat::parse_str(r#"(module (func (export "_start")))"#).unwrap();
+    let report = verify(write(dir.path(), "plain.wasm", &plain)).unwrap();
+    assert!(report.passed(), "{report}");
+    assert_eq!(report.check("manifest").unwrap().status, CheckStatus::Warn);
+    assert_eq!(report.check("metadata").unwrap().detail, "plain 0.1.0");
+    assert_eq!(report.check("memory").unwrap().status, CheckStatus::Warn);
+}
+
+#[test]
+fn verify_reports_what_keeps_a_plugin_from_loading() {
+    let dir = tempfile::tempdir().unwrap();
+    let wasm = wat::parse_str(
+        r#"(module
+             (import "env" "log" (func (param i32)))
+             (import "wasi_snapshot_preview1" "fd_advise" (func (param i32 i64 i64 i32) (result i32)))
+             (func (export "greet") (param i32)))"#,
+    )
+    .unwrap();
+    let wasm = embed_manifest(
+        &wasm,
+        r#"{"name": "bad", "version": "one", "capabilities": ["shell_exec"], "commands": ["greet"]}"#,
+    );
+    let report = verify(write(dir.path(), "bad.wasm", &wasm)).unwrap();
+    assert!(!report.passed());
+    let status = |name| report.check(name).unwrap().status;
+    assert_eq!(status("module"), CheckStatus::Pass);
+    assert_eq!(status("metadata"), CheckStatus::Fail);
+    assert_eq!(
+        report.check("capabilities").unwrap().detail,
+        "Capability 'shell_exec' is not available to WASI plugins"
+    );
+    assert_eq!(
+        report.check("imports").unwrap().detail,
+        "imports what plugins cannot use: env::log"
+    );
+    assert_eq!(status("exports"), CheckStatus::Fail);
+
+    // Exports the shell cannot call
+    let wasm = embed_manifest(
+        &wat::parse_str(r#"(module (func (export "greet") (param i32)))"#).unwrap(),
+        r#"{"name": "bad", "version": "1.0.0", "commands": ["greet"]}"#,
+    );
+    let report = verify(write(dir.path(), "bad.wasm", &wasm)).unwrap();
+    assert_eq!(
+        report.check("exports").unwrap().detail,
+        "must take no parameters and return nothing or an i32: greet"
+    );
+
+    // Stubbed WASI functions work, but not as the plugin expects
+    let wasm = wat::parse_str(
+        r#"(module
+             (import "wasi_snapshot_preview1" "fd_advise" (func (param i32 i64 i64 i32) (result i32)))
+             (func (export "_start")))"#,
+    )
+    .unwrap();
+    let report = verify(write(dir.path(), "advise.wasm", &wasm)).unwrap();
+    assert!(report.passed(), "{report}");
+    assert_eq!(
+        report.check("imports").unwrap().detail,
+        "not provided, failing with ENOSYS: fd_advise"
+    );
+
+    let report = verify(write(dir.path(), "notes.txt", b"hello")).unwrap();
+    assert_eq!(report.check("format").unwrap().status, CheckStatus::Fail);
+    let report = verify(write(dir.path(), "junk.wasm", b"hello")).unwrap();
+    assert_eq!(report.check("module").unwrap().status, CheckStatus::Fail);
+    assert!(verify(dir.path().join("missing.wasm")).is_err());
+}
@@ -0,0 +1,333 @@
+#![cfg(all(
+    feature = "plugin-management",
+    feature = "wasi-runtime",
+    feature = "remote-plugins"
+))]
+
+use base64::engine::{general_purpose::STANDARD as BASE64, Engine};
+use chrono::Utc;
+use nxsh_plugin::runtime::MANIFEST_SECTION;
+use nxsh_plugin::signature::{
+    Ed25519PrivateKey, KeyCertificate, PluginSignature, SignatureVerifier,
+};
+use nxsh_plugin::store::{PluginStore, SignatureStatus};
+use nxsh_plugin::trust::{fingerprint, Keyring, Trust, TrustLevel, Verdict, REVOCATION_FILE};
+use nxsh_plugin::PluginManager;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Nothing listens on this port, so a revocation list there cannot be fetched
+const UNREACHABLE: &str = "http://127.0.0.1:1/revocations.json";
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Serve `files` over HTTP on a local port, returning its base URL
+fn serve(files: HashMap<String, Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = Vec::new();
+            let mut byte = [0u8; 1];
+            while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                request.push(byte[0]);
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, body) = match files.get(path) {
+                Some(body) => ("200 OK", body.clone()),
+                None => ("404 Not Found", Vec::new()),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&body);
+        }
+    });
+    url
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write a plugin named `name` at `version` as `dir/file`
+fn write_plugin(dir: &Path, file: &str, name: &str, version: &str) -> PathBuf {
+    let manifest = format!(r#"{{"name": "{name}", "version": "{version}"}}"#);
+    let mut wasm = wat::parse_str(r#"(module (func (export "_start")))"#).unwrap();
+    let mut section = leb128(MANIFEST_SECTION.len());
+    section.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    section.extend_from_slice(manifest.as_bytes());
+    wasm.push(0);
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    let path = dir.join(file);
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+fn key(byte: u8) -> Ed25519PrivateKey {
+    Ed25519PrivateKey::from_bytes(&[byte; 32]).unwrap()
+}
+
+fn public(key: &Ed25519PrivateKey) -> String {
+    key.public_key().unwrap().to_base64()
+}
+
+/// Sign the plugin at `path` as `key_id`, with the certificates of `chain`
+async fn sign(
+    path: &Path,
+    key: &Ed25519PrivateKey,
+    key_id: &str,
+    chain: Vec<KeyCertificate>,
+) -> PluginSignature {
+    SignatureVerifier::new()
+        .unwrap()
+        .sign_plugin_with_chain(path, key, key_id.to_string(), chain)
+        .await
+        .unwrap()
+}
+
+fn trust(dir: &Path, revocation_url: Option<&str>) -> Trust {
+    let mut trust = Trust::new(Keyring::new(dir.join("keyring.toml")), dir.join("cache"));
+    trust.set_revocation_url(revocation_url.map(str::to_string));
+    trust.set_offline(false);
+    trust
+}
+
+/// Make the file at `path` look `age` old
+fn age(path: &Path, age: Duration) {
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() - age)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn trusts_intermediate_keys_certified_by_root_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let (root, ci) = (key(1), key(2));
+    let keyring = Keyring::new(dir.path().join("keyring.toml"));
+    keyring
+        .add("acme-root", &public(&root), TrustLevel::Root)
+        .unwrap();
+    let path = write_plugin(dir.path(), "hello.wasm", "hello", "1.0.0");
+    let bytes = std::fs::read(&path).unwrap();
+
+    let certificate = KeyCertificate::issue(
+        "acme-ci",
+        &ci.public_key().unwrap(),
+        "acme-root",
+        &root,
+        None,
+    )
+    .unwrap();
+    let signature = sign(&path, &ci, "acme-ci", vec![certificate.clone()]).await;
+    assert_eq!(
+        trust(dir.path(), None).check(&signature, &bytes, None),
+        Verdict::Verified("acme-ci".to_string())
+    );
+
+    // Without its certificate the intermediate key is not trusted
+    let unchained = sign(&path, &ci, "acme-ci", Vec::new()).await;
+    assert!(matches!(
+        trust(dir.path(), None).check(&unchained, &bytes, None),
+        Verdict::Invalid(_)
+    ));
+
+    // Only root keys certify
+    keyring.set_level("acme-root", TrustLevel::Signer).unwrap();
+    assert_eq!(
+        trust(dir.path(), None).check(&signature, &bytes, None),
+        Verdict::Invalid("acme-ci is not certified by a trusted root key".to_string())
+    );
+    keyring.set_level("acme-root", TrustLevel::Root).unwrap();
+
+    let expired = KeyCertificate::issue(
+        "acme-ci",
+        &ci.public_key().unwrap(),
+        "acme-root",
+        &root,
+        Some(Utc::now() - chrono::Duration::days(1)),
+    )
+    .unwrap();
+    let signature = sign(&path, &ci, "acme-ci", vec![expired]).await;
+    assert_eq!(
+        trust(dir.path(), None).check(&signature, &bytes, None),
+        Verdict::Invalid("the certificate of acme-ci has expired".to_string())
+    );
+
+    // A certificate issued by another key does not verify
+    let forged = KeyCertificate::issue(
+        "acme-ci",
+        &ci.public_key().unwrap(),
+        "acme-root",
+        &key(3),
+        None,
+    )
+    .unwrap();
+    let signature = sign(&path, &ci, "acme-ci", vec![forged]).await;
+    assert!(matches!(
+        trust(dir.path(), None).check(&signature, &bytes, None),
+        Verdict::Invalid(_)
+    ));
+}
+
+#[tokio::test]
+async fn refuses_keys_in_the_revocation_list() {
+    let downloads = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let manager = PluginManager::new();
+    let (root, signer) = (key(1), key(2));
+    let list = format!(
+        r#"{{"revoked": [{{"fingerprint": "{}", "reason": "key leaked"}}]}}"#,
+        fingerprint(&public(&signer))
+    );
+    let url = serve(HashMap::from([
+        ("/revocations.json".to_string(), list.clone().into_bytes()),
+        (
+            "/revocations.json.sig".to_string(),
+            BASE64
+                .encode(root.sign(list.as_bytes()).unwrap())
+                .into_bytes(),
+        ),
+    ]));
+    let url = format!("{url}/revocations.json");
+    let store = PluginStore::new(dir.path()).with_revocation_url(Some(url.clone()));
+    store
+        .keyring()
+        .add("root", &public(&root), TrustLevel::Root)
+        .unwrap();
+    store
+        .keyring()
+        .add("signer", &public(&signer), TrustLevel::Signer)
+        .unwrap();
+
+    let path = write_plugin(downloads.path(), "hello.wasm", "hello", "1.0.0");
+    sign(&path, &signer, "signer", Vec::new()).await;
+    let error = store
+        .install(&manager, path.to_str().unwrap(), None)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "hello is signed with revoked key signer: key leaked"
+    );
+    assert!(store.list().unwrap().is_empty());
+
+    let plugin = store
+        .clone()
+        .allow_revoked(true)
+        .install(&manager, path.to_str().unwrap(), None)
+        .unwrap();
+    assert!(plugin.allow_revoked);
+    assert_eq!(
+        store.signature_status(&plugin),
+        SignatureStatus::Revoked {
+            key: "signer".to_string(),
+            reason: "key leaked".to_string(),
+        }
+    );
+    let installed = dir.path().join("hello.wasm");
+    store.check_revoked(&installed).unwrap();
+
+    // A plugin put in the directory by hand is not loaded
+    std::fs::copy(&installed, dir.path().join("manual.wasm")).unwrap();
+    std::fs::copy(dir.path().join("hello.sig"), dir.path().join("manual.sig")).unwrap();
+    let error = store
+        .check_revoked(&dir.path().join("manual.wasm"))
+        .unwrap_err();
+    assert!(error.to_string().contains("revoked key signer"), "{error}");
+
+    // Signatures by keys that are not revoked verify
+    sign(&path, &root, "root", Vec::new()).await;
+    let bytes = std::fs::read(&path).unwrap();
+    let signature: PluginSignature =
+        serde_json::from_slice(&std::fs::read(path.with_extension("sig")).unwrap()).unwrap();
+    let trust = |url: &str| {
+        let mut trust = Trust::new(store.keyring().clone(), dir.path().join(".cache/.trust"));
+        trust.set_revocation_url(Some(url.to_string()));
+        trust.set_offline(false);
+        trust
+    };
+    assert_eq!(
+        trust(&url).check(&signature, &bytes, None),
+        Verdict::Verified("root".to_string())
+    );
+
+    // The cached list stands in for one that cannot be fetched for a while
+    let cache = dir.path().join(".cache/.trust").join(REVOCATION_FILE);
+    age(&cache, 2 * DAY);
+    assert_eq!(
+        trust(UNREACHABLE).check(&signature, &bytes, None),
+        Verdict::Verified("root".to_string())
+    );
+    age(&cache, 8 * DAY);
+    match trust(UNREACHABLE).check(&signature, &bytes, None) {
+        Verdict::Revoked { key, reason } => {
+            assert_eq!(key, "root");
+            assert!(
+                reason.starts_with("the revocation list is 8 days old"),
+                "{reason}"
+            );
+        }
+        verdict => panic!("{verdict:?}"),
+    }
+}
+
+#[tokio::test]
+async fn signatures_cannot_be_trusted_without_a_revocation_list() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = key(1);
+    Keyring::new(dir.path().join("keyring.toml"))
+        .add("root", &public(&root), TrustLevel::Root)
+        .unwrap();
+    let path = write_plugin(dir.path(), "hello.wasm", "hello", "1.0.0");
+    let bytes = std::fs::read(&path).unwrap();
+    let signature = sign(&path, &root, "root", Vec::new()).await;
+
+    match trust(dir.path(), Some(UNREACHABLE)).check(&signature, &bytes, None) {
+        Verdict::Revoked { key, reason } => {
+            assert_eq!(key, "root");
+            assert!(
+                reason.starts_with("the revocation list cannot be fetched"),
+                "{reason}"
+            );
+        }
+        verdict => panic!("{verdict:?}"),
+    }
+
+    // A list not signed by a root key is not used
+    let list = r#"{"revoked": []}"#;
+    let url = serve(HashMap::from([
+        ("/revocations.json".to_string(), list.as_bytes().to_vec()),
+        (
+            "/revocations.json.sig".to_string(),
+            BASE64
+                .encode(key(9).sign(list.as_bytes()).unwrap())
+                .into_bytes(),
+        ),
+    ]));
+    let url = format!("{url}/revocations.json");
+    assert!(matches!(
+        trust(dir.path(), Some(&url)).check(&signature, &bytes, None),
+        Verdict::Revoked { .. }
+    ));
+    assert!(!dir.path().join("cache").join(REVOCATION_FILE).exists());
+}
@@ -0,0 +1,294 @@
+#![cfg(feature = "wasi-runtime")]
+
+use nxsh_plugin::runtime::{CompleterSpec, MANIFEST_SECTION};
+use nxsh_plugin::PluginManager;
+use std::collections::HashMap;
+use std::path::Path;
+
+const HELLO: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "hello\n")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 6))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8))))
+  (func (export "fail") (call $proc_exit (i32.const 3)))
+  (func (export "crash") unreachable))
+"#;
+
+/// A plugin completing pods, which checks it was given the line and the
+/// cursor, and printing a context for the prompt
+const KUBE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 64) "web-1\n\nweb-2\n")
+  (data (i32.const 96) "prod\nstaging\n")
+  (func $print (param i32 i32)
+    (i32.store (i32.const 0) (local.get 0))
+    (i32.store (i32.const 4) (local.get 1))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8))))
+  (func (export "pods")
+    (drop (call $args_sizes (i32.const 16) (i32.const 20)))
+    (if (i32.ne (i32.load (i32.const 16)) (i32.const 3)) (then unreachable))
+    (call $print (i32.const 64) (i32.const 13)))
+  (func (export "kube_context") (call $print (i32.const 96) (i32.const 13)))
+  (func (export "_start")))
+"#;
+
+/// A plugin whose commands return what WASI reports about fd 3 and the
+/// environment
+fn probe(prefix: &str) -> String {
+    format!(
+        r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_prestat_get" (func $prestat (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_sizes_get" (func $sizes (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "{prefix}_preopen") (result i32)
+    (call $prestat (i32.const 3) (i32.const 0)))
+  (func (export "{prefix}_env") (result i32)
+    (drop (call $sizes (i32.const 0) (i32.const 4)))
+    (i32.load (i32.const 0))))
+"#
+    )
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Write `wat` as a plugin with `manifest` in its custom section
+fn write_plugin(dir: &Path, file: &str, wat: &str, manifest: &str) -> std::path::PathBuf {
+    let mut wasm = wat::parse_str(wat).unwrap();
+    let mut section = leb128(MANIFEST_SECTION.len());
+    section.extend_from_slice(MANIFEST_SECTION.as_bytes());
+    section.extend_from_slice(manifest.as_bytes());
+    wasm.push(0);
+    wasm.extend(leb128(section.len()));
+    wasm.extend(section);
+    let path = dir.join(file);
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+async fn manager() -> PluginManager {
+    let mut manager = PluginManager::new();
+    manager.initialize_runtimes().await.unwrap();
+    manager
+}
+
+#[tokio::test]
+async fn runs_exported_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_plugin(
+        dir.path(),
+        "hello.wasm",
+        HELLO,
+        r#"{"name": "hello", "version": "1.0.0", "commands": [
+            "crash",
+            {"name": "fail", "synopsis": "Exit with status 3", "usage": "fail [FILE]",
+             "flags": [["-q", "Quietly"]]},
+            {"name": "oops", "export": "crash"}]}"#,
+    );
+    let mut manager = manager().await;
+    let id = manager.load_plugin(&path).await.unwrap();
+    assert_eq!(id, "hello@1.0.0");
+
+    let runtime = manager.wasi_runtime().unwrap();
+    assert_eq!(runtime.commands().await, ["crash", "fail", "hello", "oops"]);
+    let specs = runtime.command_specs().await;
+    let (plugin, fail) = &specs[1];
+    assert_eq!(plugin, "hello@1.0.0");
+    assert_eq!(fail.synopsis, "Exit with status 3");
+    assert_eq!(fail.usage, "fail [FILE]");
+    assert_eq!(fail.flags, [("-q".to_string(), "Quietly".to_string())]);
+    assert_eq!(specs[2].1.export.as_deref(), Some("_start"));
+
+    let env = HashMap::new();
+    let output = runtime
+        .run_command("hello", &[], &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout, b"hello\n");
+
+    let output = runtime
+        .run_command("fail", &[], &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(output.status, 3);
+
+    let output = runtime
+        .run_command("crash", &[], &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(output.status, 134);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("crash: plugin 'hello@1.0.0' trapped: "));
+
+    let output = runtime
+        .run_command("oops", &[], &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(output.status, 134);
+
+    manager.unload_plugin(&id).await.unwrap();
+    assert!(manager.wasi_runtime().unwrap().commands().await.is_empty());
+}
+
+#[tokio::test]
+async fn capabilities_map_to_preopens_and_environment() {
+    let dir = tempfile::tempdir().unwrap();
+    let closed = write_plugin(
+        dir.path(),
+        "closed.wasm",
+        &probe("closed"),
+        r#"{"name": "closed", "commands": ["closed_preopen", "closed_env"]}"#,
+    );
+    let open = write_plugin(
+        dir.path(),
+        "open.wasm",
+        &probe("open"),
+        r#"{"name": "open", "capabilities": ["file_read", "env_read"],
+            "commands": ["open_preopen", "open_env"]}"#,
+    );
+    let mut manager = manager().await;
+    manager.load_plugin(&closed).await.unwrap();
+    manager.load_plugin(&open).await.unwrap();
+    let runtime = manager.wasi_runtime().unwrap();
+
+    let env = HashMap::from([
+        ("HOME".to_string(), "/home/u".to_string()),
+        ("LANG".to_string(), "C".to_string()),
+    ]);
+    let status = |command: &'static str| {
+        let env = env.clone();
+        let cwd = dir.path().to_path_buf();
+        async move {
+            runtime
+                .run_command(command, &[], &env, &cwd)
+                .await
+                .unwrap()
+                .status
+        }
+    };
+    // EBADF without file_read, then the preopened working directory
+    assert_eq!(status("closed_preopen").await, 8);
+    assert_eq!(status("open_preopen").await, 0);
+    assert_eq!(status("closed_env").await, 0);
+    assert_eq!(status("open_env").await, 2);
+}
+
+#[tokio::test]
+async fn rejects_plugins_it_cannot_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut manager = manager().await;
+
+    let path = write_plugin(
+        dir.path(),
+        "spawn.wasm",
+        HELLO,
+        r#"{"name": "spawn", "capabilities": ["command_execute"]}"#,
+    );
+    let error = manager.load_plugin(&path).await.unwrap_err();
+    assert!(error.to_string().contains("not available to WASI plugins"));
+    assert!(manager.wasi_runtime().unwrap().commands().await.is_empty());
+
+    let path = write_plugin(
+        dir.path(),
+        "host.wasm",
+        r#"(module (import "env" "system" (func (param i32))) (func (export "_start")))"#,
+        r#"{"name": "host"}"#,
+    );
+    let error = manager.load_plugin(&path).await.unwrap_err();
+    assert!(format!("{error:#}").contains("imports env::system, which plugins cannot use"));
+}
+
+#[tokio::test]
+async fn completes_arguments_and_renders_prompt_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_plugin(
+        dir.path(),
+        "kube.wasm",
+        KUBE,
+        r#"{"name": "kube", "version": "1.0.0",
+            "completers": [{"command": "kubectl", "export": "pods"}],
+            "segments": [{"name": "kube", "export": "kube_context", "timeout_ms": 100},
+                         {"name": "kube_context"}]}"#,
+    );
+    let mut manager = manager().await;
+    let id = manager.load_plugin(&path).await.unwrap();
+
+    let runtime = manager.wasi_runtime().unwrap();
+    assert_eq!(
+        runtime.completers().await,
+        [(
+            id.clone(),
+            CompleterSpec {
+                command: "kubectl".to_string(),
+                export: "pods".to_string(),
+            }
+        )]
+    );
+    let segments = runtime.segments().await;
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].1.timeout_ms, Some(100));
+    assert_eq!(segments[1].1.export(), "kube_context");
+
+    let env = HashMap::new();
+    let line = "kubectl logs w";
+    let candidates = runtime
+        .complete("kubectl", line, line.len(), &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(candidates, ["web-1", "web-2"]);
+    assert!(runtime
+        .complete("kubectx", line, line.len(), &env, dir.path())
+        .await
+        .is_err());
+    let context = runtime
+        .render_segment("kube", &env, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(context, "prod");
+
+    // Another plugin cannot complete the same command
+    let other = write_plugin(
+        dir.path(),
+        "other.wasm",
+        KUBE,
+        r#"{"name": "other", "version": "1.0.0",
+            "completers": [{"command": "kubectl", "export": "pods"}]}"#,
+    );
+    let error = manager.load_plugin(&other).await.unwrap_err();
+    assert!(format!("{error:#}").contains("Completion for 'kubectl' of plugin"));
+
+    // Nor can a plugin list an export it does not have
+    let missing = write_plugin(
+        dir.path(),
+        "missing.wasm",
+        KUBE,
+        r#"{"name": "missing", "version": "1.0.0", "segments": [{"name": "nope"}]}"#,
+    );
+    let error = manager.load_plugin(&missing).await.unwrap_err();
+    assert!(format!("{error:#}").contains("exports no function 'nope'"));
+
+    manager.unload_plugin(&id).await.unwrap();
+    let runtime = manager.wasi_runtime().unwrap();
+    assert!(runtime.completers().await.is_empty());
+    assert!(runtime.segments().await.is_empty());
+}
@@ -0,0 +1,236 @@
+//! Fish-style abbreviations
+//!
+//! An abbreviation is a word the line editor replaces in place when Space or
+//! Enter follows it, so unlike an alias the expansion is visible and can be
+//! edited before it runs. Abbreviations are defined with the `abbr` builtin,
+//! usually from `~/.nxshrc`, and kept in a registry shared by the builtin and
+//! the editor for the rest of the session.
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+/// Where an abbreviation may be expanded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AbbrPosition {
+    /// Only as the first word of a command
+    #[default]
+    Command,
+    /// As any word on the line
+    Anywhere,
+}
+
+/// One abbreviation: `name` expands to `expansion`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Abbreviation {
+    pub name: String,
+    pub expansion: String,
+    pub position: AbbrPosition,
+    /// The cursor goes where this marker is in the expansion, and the marker
+    /// is removed
+    pub cursor_marker: Option<String>,
+}
+
+impl Abbreviation {
+    pub fn new(name: impl Into<String>, expansion: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            expansion: expansion.into(),
+            position: AbbrPosition::Command,
+            cursor_marker: None,
+        }
+    }
+
+    /// Text to insert, and the byte offset within it for the cursor when
+    /// the expansion places it
+    fn text_and_cursor(&self) -> (String, Option<usize>) {
+        match &self.cursor_marker {
+            Some(marker) if !marker.is_empty() => match self.expansion.find(marker.as_str()) {
+                Some(at) => {
+                    let mut text = self.expansion.clone();
+                    text.replace_range(at..at + marker.len(), "");
+                    (text, Some(at))
+                }
+                None => (self.expansion.clone(), None),
+            },
+            _ => (self.expansion.clone(), None),
+        }
+    }
+}
+
+/// A line with an abbreviation expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub line: String,
+    pub cursor: usize,
+    /// The expansion chose the cursor position, so the key that triggered
+    /// it should not also insert a space or run the line
+    pub positioned: bool,
+}
+
+/// Abbreviations by name
+#[derive(Debug, Clone, Default)]
+pub struct Abbreviations {
+    entries: BTreeMap<String, Abbreviation>,
+}
+
+impl Abbreviations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `abbr`, replacing one of the same name
+    pub fn add(&mut self, abbr: Abbreviation) {
+        self.entries.insert(abbr.name.clone(), abbr);
+    }
+
+    /// Remove the abbreviation called `name`; false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// Give abbreviation `from` the name `to`; false if there was none
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        match self.entries.remove(from) {
+            Some(mut abbr) => {
+                abbr.name = to.to_string();
+                self.entries.insert(to.to_string(), abbr);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Abbreviation> {
+        self.entries.get(name)
+    }
+
+    /// Abbreviations in name order
+    pub fn iter(&self) -> impl Iterator<Item = &Abbreviation> {
+        self.entries.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Expand the word that ends at `cursor`, if it is an abbreviation in a
+    /// position it may expand in. Words inside quotes are left alone.
+    pub fn expand(&self, line: &str, cursor: usize) -> Option<Expansion> {
+        if self.entries.is_empty() || cursor > line.len() || !line.is_char_boundary(cursor) {
+            return None;
+        }
+        // The cursor must sit at the end of the word
+        if line[cursor..]
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace())
+        {
+            return None;
+        }
+        let before = &line[..cursor];
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || is_separator(c))
+            .map(|i| i + before[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0);
+        let word = &before[start..];
+        let abbr = self.entries.get(word)?;
+
+        let prefix = &line[..start];
+        if in_quotes(prefix) {
+            return None;
+        }
+        if abbr.position == AbbrPosition::Command && !command_position(prefix) {
+            return None;
+        }
+
+        let (text, offset) = abbr.text_and_cursor();
+        let mut expanded = String::with_capacity(line.len() + text.len());
+        expanded.push_str(prefix);
+        expanded.push_str(&text);
+        expanded.push_str(&line[cursor..]);
+        Some(Expansion {
+            line: expanded,
+            cursor: start + offset.unwrap_or(text.len()),
+            positioned: offset.is_some(),
+        })
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, ';' | '|' | '&' | '(' | ')')
+}
+
+/// True when a word after `prefix` starts a command
+fn command_position(prefix: &str) -> bool {
+    let prefix = prefix.trim_end();
+    prefix.is_empty() || prefix.ends_with(|c: char| matches!(c, ';' | '|' | '&' | '('))
+}
+
+/// True when `prefix` leaves a quote open
+fn in_quotes(prefix: &str) -> bool {
+    let mut single = false;
+    let mut double = false;
+    let mut escaped = false;
+    for c in prefix.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            _ => {}
+        }
+    }
+    single || double
+}
+
+static SHARED: OnceLock<RwLock<Abbreviations>> = OnceLock::new();
+
+/// The session's abbreviations, shared by the `abbr` builtin and the editor
+pub fn shared() -> &'static RwLock<Abbreviations> {
+    SHARED.get_or_init(|| RwLock::new(Abbreviations::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Abbreviations {
+        let mut abbrs = Abbreviations::new();
+        abbrs.add(Abbreviation::new("gs", "git status"));
+        abbrs.add(Abbreviation {
+            cursor_marker: Some("%".into()),
+            ..Abbreviation::new("gc", "git commit -m \"%\"")
+        });
+        abbrs.add(Abbreviation {
+            position: AbbrPosition::Anywhere,
+            ..Abbreviation::new("L", "| less")
+        });
+        abbrs
+    }
+
+    #[test]
+    fn expands_in_command_position_only() {
+        let abbrs = table();
+        let expanded = abbrs.expand("gs", 2).unwrap();
+        assert_eq!(expanded.line, "git status");
+        assert_eq!(expanded.cursor, 10);
+        assert!(!expanded.positioned);
+
+        assert_eq!(
+            abbrs.expand("ls && gs", 8).unwrap().line,
+            "ls && git status"
+        );
+        assert!(abbrs.expand("echo gs", 7).is_none());
+        assert!(abbrs.expand("echo 'a gs", 10).is_none());
+        assert!(abbrs.expand("gsx", 3).is_none());
+        assert_eq!(abbrs.expand("cat f L", 7).unwrap().line, "cat f | less");
+    }
+
+    #[test]
+    fn cursor_marker_places_the_cursor() {
+        let expanded = table().expand("gc", 2).unwrap();
+        assert_eq!(expanded.line, "git commit -m \"\"");
+        assert_eq!(expanded.cursor, 15);
+        assert!(expanded.positioned);
+    }
+}
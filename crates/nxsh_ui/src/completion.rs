@@ -3,8 +3,11 @@
 //! This module provides context-aware completion for commands, files, variables,
 //! and more, with fuzzy matching and smart filtering capabilities.
 //! Pure cross-platform implementation using only crossterm and standard library.
+//! Plugins complete the arguments of the commands they register a
+//! completer for, see [`NexusCompleter::set_plugins`].
 
-use std::process::Command;
+use nxsh_core::{PluginCommandRegistry, PluginCompleter};
+use std::process::{Command, Stdio};
 use std::{
     collections::{HashMap, HashSet},
     env, fs,
@@ -64,6 +67,8 @@ pub struct NexusCompleter {
     completion_config: CompletionConfig,
     system_scanned: bool,
     command_specs: HashMap<String, CommandSpec>,
+    /// Completers registered by plugins
+    plugins: PluginCommandRegistry,
 }
 
 #[derive(Debug, Clone)]
@@ -75,6 +80,21 @@ struct CommandSpec {
     default_arg: ArgKind,
     // Map flag -> expected value kind (e.g., --file <path>)
     flag_value_kind: HashMap<&'static str, ArgKind>,
+    // Flags and argument kind once a subcommand has been typed
+    subcommand_specs: HashMap<&'static str, SubcommandSpec>,
+}
+
+#[derive(Debug, Clone)]
+struct SubcommandSpec {
+    flags: Vec<(&'static str, &'static str)>,
+    arg: ArgKind,
+}
+
+fn sub(flags: &[(&'static str, &'static str)], arg: ArgKind) -> SubcommandSpec {
+    SubcommandSpec {
+        flags: flags.to_vec(),
+        arg,
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -84,9 +104,62 @@ enum ArgKind {
     File,
     Dir,
     Env,
+    /// Variable names, without `$`
+    Variable,
+    /// Command names
+    Command,
+    /// The lines a command prints, e.g. git branches or docker containers
+    Dynamic(&'static [&'static str]),
     None,
 }
 
+const GIT_REFS: &[&str] = &[
+    "git",
+    "for-each-ref",
+    "--format=%(refname:short)",
+    "refs/heads",
+    "refs/tags",
+    "refs/remotes",
+];
+const GIT_REMOTES: &[&str] = &["git", "remote"];
+const DOCKER_IMAGES: &[&str] = &["docker", "images", "--format", "{{.Repository}}:{{.Tag}}"];
+const DOCKER_CONTAINERS: &[&str] = &["docker", "ps", "-a", "--format", "{{.Names}}"];
+
+impl ArgKind {
+    /// What a builtin's arguments are, from the placeholders in its usage
+    /// line: `cd [DIRECTORY]` takes directories, `help [COMMAND]` command
+    /// names, `pwd` nothing. A placeholder that names a kind wins over ones
+    /// that do not, so `grep [OPTIONS] PATTERN [FILE...]` takes files.
+    fn from_usage(usage: &str) -> Self {
+        let mut words = usage.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let mut kind = ArgKind::None;
+        for word in words {
+            let placeholder: String = word
+                .chars()
+                .skip_while(|c| !c.is_ascii_uppercase())
+                .take_while(|c| c.is_ascii_uppercase() || *c == '_')
+                .collect();
+            let named = match placeholder.as_str() {
+                "" | "OPTIONS" => continue,
+                "FILE" | "FILES" | "SOURCE" | "DEST" | "INPUT" | "OUTPUT" | "TARGET"
+                | "LINK_NAME" | "ZIPFILE" => ArgKind::File,
+                "DIRECTORY" | "DIR" => ArgKind::Dir,
+                "PATH" => ArgKind::Path,
+                "COMMAND" => ArgKind::Command,
+                "VAR" => ArgKind::Variable,
+                // `unset NAME`, `export NAME=VALUE`; alias names are not variables
+                "NAME" if !command.contains("alias") => ArgKind::Variable,
+                _ => ArgKind::Any,
+            };
+            if named != ArgKind::Any || kind == ArgKind::None {
+                kind = named;
+            }
+        }
+        kind
+    }
+}
+
 impl NexusCompleter {
     /// Create a new completer
     pub fn new() -> Self {
@@ -98,6 +171,7 @@ impl NexusCompleter {
             completion_config: CompletionConfig::default(),
             system_scanned: false,
             command_specs: HashMap::new(),
+            plugins: PluginCommandRegistry::default(),
         };
 
         // Initialize with basic builtins
@@ -136,6 +210,39 @@ impl NexusCompleter {
         }
     }
 
+    /// Add completion metadata for a builtin: its description for command
+    /// completion, its flags, and the kind of argument its usage line takes
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        description: &str,
+        usage: &str,
+        flags: &[(String, String)],
+    ) {
+        self.builtin_cache
+            .insert(name.to_string(), description.to_string());
+        let spec = self
+            .command_specs
+            .entry(name.to_string())
+            .or_insert_with(|| CommandSpec {
+                name: name.to_string(),
+                subcommands: vec![],
+                flags: vec![],
+                default_arg: ArgKind::from_usage(usage),
+                flag_value_kind: HashMap::new(),
+                subcommand_specs: HashMap::new(),
+            });
+        for (flag, desc) in flags {
+            if spec.flags.iter().any(|(known, _)| known == flag) {
+                continue;
+            }
+            // Leaked like discovered specs: a small, process-lifetime table
+            let f: &'static str = Box::leak(flag.clone().into_boxed_str());
+            let d: &'static str = Box::leak(desc.clone().into_boxed_str());
+            spec.flags.push((f, d));
+        }
+    }
+
     fn init_command_specs(&mut self) {
         use ArgKind::*;
         let mut add = |spec: CommandSpec| {
@@ -148,6 +255,7 @@ impl NexusCompleter {
             flags: vec![],
             default_arg: Dir,
             flag_value_kind: HashMap::new(),
+            subcommand_specs: HashMap::new(),
         });
 
         add(CommandSpec {
@@ -162,6 +270,7 @@ impl NexusCompleter {
             ],
             default_arg: Path,
             flag_value_kind: HashMap::new(),
+            subcommand_specs: HashMap::new(),
         });
 
         add(CommandSpec {
@@ -170,6 +279,7 @@ impl NexusCompleter {
             flags: vec![],
             default_arg: File,
             flag_value_kind: HashMap::new(),
+            subcommand_specs: HashMap::new(),
         });
 
         add(CommandSpec {
@@ -178,8 +288,11 @@ impl NexusCompleter {
             flags: vec![("-n", "no newline")],
             default_arg: Any,
             flag_value_kind: HashMap::new(),
+            subcommand_specs: HashMap::new(),
         });
 
+        let git_refs = Dynamic(GIT_REFS);
+        let git_remotes = Dynamic(GIT_REMOTES);
         add(CommandSpec {
             name: "git".into(),
             subcommands: vec![
@@ -187,40 +300,748 @@ impl NexusCompleter {
                 ("commit", "Record changes to the repository"),
                 ("status", "Show the working tree status"),
                 ("checkout", "Switch branches or restore files"),
+                ("switch", "Switch branches"),
                 ("branch", "List, create, or delete branches"),
                 ("push", "Update remote refs along with objects"),
                 ("pull", "Fetch from and integrate with another repo"),
+                ("fetch", "Download objects and refs from another repo"),
                 ("clone", "Clone a repository into a new directory"),
+                ("init", "Create an empty Git repository"),
                 ("merge", "Join two or more development histories"),
+                ("rebase", "Reapply commits on top of another base"),
+                ("diff", "Show changes between commits and the tree"),
+                ("log", "Show commit logs"),
+                ("show", "Show commits and other objects"),
+                ("reset", "Reset the current HEAD to a state"),
+                ("restore", "Restore working tree files"),
+                ("stash", "Stash away changes in the working tree"),
+                ("tag", "Create, list or delete tags"),
+                ("remote", "Manage tracked repositories"),
+                ("rm", "Remove files from the tree and the index"),
+                ("mv", "Move or rename a file"),
+                ("cherry-pick", "Apply the changes of existing commits"),
+                ("revert", "Revert existing commits"),
+                ("blame", "Show who last changed each line"),
             ],
             flags: vec![
                 ("-h", "help"),
                 ("--help", "help"),
                 ("-v", "verbose"),
                 ("--verbose", "verbose"),
+                ("-C", "run as if started in this directory"),
+                ("--version", "print the git version"),
             ],
             default_arg: Path,
-            flag_value_kind: HashMap::new(),
+            flag_value_kind: HashMap::from_iter([("-C", Dir)]),
+            subcommand_specs: HashMap::from_iter([
+                (
+                    "add",
+                    sub(
+                        &[
+                            ("-A", "add all changes"),
+                            ("--all", "add all changes"),
+                            ("-p", "choose hunks interactively"),
+                            ("--patch", "choose hunks interactively"),
+                            ("-u", "add changes to tracked files"),
+                            ("-n", "dry run"),
+                            ("-f", "add ignored files"),
+                        ],
+                        Path,
+                    ),
+                ),
+                (
+                    "commit",
+                    sub(
+                        &[
+                            ("-m", "commit message"),
+                            ("--message", "commit message"),
+                            ("-a", "commit all tracked changes"),
+                            ("--amend", "amend the previous commit"),
+                            ("--no-edit", "keep the message"),
+                            ("--fixup", "make a fixup commit"),
+                            ("-S", "sign the commit"),
+                            ("-v", "show the diff in the editor"),
+                        ],
+                        Path,
+                    ),
+                ),
+                (
+                    "checkout",
+                    sub(
+                        &[
+                            ("-b", "create a branch"),
+                            ("-B", "create or reset a branch"),
+                            ("--track", "set upstream"),
+                            ("-f", "discard local changes"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "switch",
+                    sub(
+                        &[
+                            ("-c", "create a branch"),
+                            ("-C", "create or reset a branch"),
+                            ("--detach", "detach HEAD at the commit"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "branch",
+                    sub(
+                        &[
+                            ("-a", "list local and remote branches"),
+                            ("-r", "list remote branches"),
+                            ("-d", "delete a merged branch"),
+                            ("-D", "delete a branch"),
+                            ("-m", "rename a branch"),
+                            ("-v", "show the last commit"),
+                            ("--list", "list branches"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "push",
+                    sub(
+                        &[
+                            ("-u", "set upstream"),
+                            ("--set-upstream", "set upstream"),
+                            ("-f", "force"),
+                            ("--force-with-lease", "force if the remote is as expected"),
+                            ("--tags", "push tags"),
+                            ("-d", "delete remote refs"),
+                            ("--dry-run", "dry run"),
+                        ],
+                        git_remotes,
+                    ),
+                ),
+                (
+                    "pull",
+                    sub(
+                        &[
+                            ("--rebase", "rebase instead of merging"),
+                            ("--no-rebase", "merge"),
+                            ("--ff-only", "only fast-forward"),
+                            ("--tags", "fetch tags"),
+                        ],
+                        git_remotes,
+                    ),
+                ),
+                (
+                    "fetch",
+                    sub(
+                        &[
+                            ("--all", "fetch all remotes"),
+                            ("--prune", "drop deleted remote branches"),
+                            ("--tags", "fetch tags"),
+                            ("--depth", "limit history depth"),
+                        ],
+                        git_remotes,
+                    ),
+                ),
+                (
+                    "clone",
+                    sub(
+                        &[
+                            ("--depth", "limit history depth"),
+                            ("-b", "branch to check out"),
+                            ("--branch", "branch to check out"),
+                            ("--recursive", "clone submodules"),
+                            ("--bare", "make a bare repository"),
+                            ("--single-branch", "clone one branch"),
+                        ],
+                        Any,
+                    ),
+                ),
+                (
+                    "init",
+                    sub(
+                        &[
+                            ("--bare", "make a bare repository"),
+                            ("-b", "initial branch name"),
+                            ("--initial-branch", "initial branch name"),
+                        ],
+                        Dir,
+                    ),
+                ),
+                (
+                    "merge",
+                    sub(
+                        &[
+                            ("--no-ff", "always make a merge commit"),
+                            ("--ff-only", "only fast-forward"),
+                            ("--squash", "squash into one change"),
+                            ("--abort", "abort the merge"),
+                            ("--continue", "continue the merge"),
+                            ("-m", "merge message"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "rebase",
+                    sub(
+                        &[
+                            ("-i", "rebase interactively"),
+                            ("--interactive", "rebase interactively"),
+                            ("--onto", "new base"),
+                            ("--continue", "continue the rebase"),
+                            ("--abort", "abort the rebase"),
+                            ("--skip", "skip the current commit"),
+                            ("--autosquash", "apply fixup commits"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "diff",
+                    sub(
+                        &[
+                            ("--staged", "diff the index"),
+                            ("--cached", "diff the index"),
+                            ("--stat", "show a diffstat"),
+                            ("--name-only", "show changed file names"),
+                            ("--word-diff", "diff by word"),
+                        ],
+                        Path,
+                    ),
+                ),
+                (
+                    "log",
+                    sub(
+                        &[
+                            ("--oneline", "one line per commit"),
+                            ("--graph", "draw the history graph"),
+                            ("--all", "all refs"),
+                            ("-p", "show patches"),
+                            ("--stat", "show diffstats"),
+                            ("-n", "limit the number of commits"),
+                            ("--author", "commits by author"),
+                            ("--since", "commits since a date"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "show",
+                    sub(
+                        &[
+                            ("--stat", "show a diffstat"),
+                            ("--name-only", "show changed file names"),
+                            ("--oneline", "short header"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "reset",
+                    sub(
+                        &[
+                            ("--soft", "keep the index and tree"),
+                            ("--mixed", "reset the index"),
+                            ("--hard", "reset the index and tree"),
+                            ("-p", "choose hunks interactively"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "restore",
+                    sub(
+                        &[
+                            ("--staged", "restore the index"),
+                            ("--worktree", "restore the working tree"),
+                            ("--source", "restore from this commit"),
+                            ("-p", "choose hunks interactively"),
+                        ],
+                        Path,
+                    ),
+                ),
+                (
+                    "stash",
+                    sub(
+                        &[
+                            ("-u", "include untracked files"),
+                            ("--include-untracked", "include untracked files"),
+                            ("-m", "stash message"),
+                            ("-p", "choose hunks interactively"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "tag",
+                    sub(
+                        &[
+                            ("-a", "annotated tag"),
+                            ("-d", "delete tags"),
+                            ("-l", "list tags"),
+                            ("-m", "tag message"),
+                            ("-s", "signed tag"),
+                            ("-f", "replace an existing tag"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                ("remote", sub(&[("-v", "show URLs")], git_remotes)),
+                (
+                    "rm",
+                    sub(
+                        &[
+                            ("-r", "remove recursively"),
+                            ("-f", "force"),
+                            ("--cached", "only remove from the index"),
+                            ("-n", "dry run"),
+                        ],
+                        Path,
+                    ),
+                ),
+                (
+                    "mv",
+                    sub(
+                        &[("-f", "force"), ("-n", "dry run"), ("-v", "verbose")],
+                        Path,
+                    ),
+                ),
+                (
+                    "cherry-pick",
+                    sub(
+                        &[
+                            ("-n", "do not commit"),
+                            ("-x", "record the original commit"),
+                            ("--continue", "continue"),
+                            ("--abort", "abort"),
+                            ("--skip", "skip the current commit"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "revert",
+                    sub(
+                        &[
+                            ("-n", "do not commit"),
+                            ("--no-edit", "keep the message"),
+                            ("--continue", "continue"),
+                            ("--abort", "abort"),
+                        ],
+                        git_refs,
+                    ),
+                ),
+                (
+                    "blame",
+                    sub(
+                        &[
+                            ("-L", "line range"),
+                            ("-w", "ignore whitespace"),
+                            ("-C", "detect moved lines"),
+                        ],
+                        File,
+                    ),
+                ),
+            ]),
         });
 
+        let cargo_build = [
+            ("--release", "optimized build"),
+            ("-p", "select package (value)"),
+            ("--package", "select package (value)"),
+            ("--workspace", "all workspace members"),
+            ("--all-targets", "all targets"),
+            ("--features", "features to enable (value)"),
+            ("--all-features", "enable all features"),
+            ("--no-default-features", "disable default features"),
+            ("--target", "target triple (value)"),
+            ("--lib", "the library"),
+            ("--bin", "select binary (value)"),
+            ("--example", "select example (value)"),
+            ("-j", "parallel jobs (value)"),
+        ];
+        let cargo_test = [
+            &cargo_build[..],
+            &[
+                ("--doc", "documentation tests"),
+                ("--test", "select integration test (value)"),
+                ("--no-run", "build but do not run"),
+                ("--no-fail-fast", "run every test"),
+            ],
+        ]
+        .concat();
         add(CommandSpec {
             name: "cargo".into(),
             subcommands: vec![
                 ("build", "Compile the current package"),
+                ("check", "Check the package for errors"),
                 ("run", "Run a binary or example"),
                 ("test", "Run tests"),
                 ("bench", "Run benchmarks"),
                 ("doc", "Build documentation"),
                 ("clean", "Remove generated artifacts"),
+                ("new", "Create a new package"),
+                ("init", "Create a package in a directory"),
+                ("add", "Add dependencies"),
+                ("remove", "Remove dependencies"),
+                ("update", "Update dependencies in Cargo.lock"),
+                ("install", "Install a binary"),
+                ("uninstall", "Remove an installed binary"),
+                ("fmt", "Format the code"),
+                ("clippy", "Run lints"),
+                ("fix", "Apply compiler suggestions"),
+                ("tree", "Show the dependency tree"),
+                ("publish", "Upload the package to a registry"),
+                ("search", "Search registry crates"),
             ],
             flags: vec![
                 ("-q", "quiet"),
+                ("-v", "verbose"),
+                ("--version", "print the cargo version"),
+                ("--list", "list commands"),
+                ("--locked", "require Cargo.lock to be up to date"),
+                ("--offline", "do not use the network"),
                 ("--release", "optimized build"),
                 ("--bin", "select binary (value)"),
                 ("--example", "select example (value)"),
             ],
             default_arg: Any,
             flag_value_kind: HashMap::from_iter([("--bin", Any), ("--example", Any)]),
+            subcommand_specs: HashMap::from_iter([
+                ("build", sub(&cargo_build, None)),
+                ("check", sub(&cargo_build, None)),
+                ("run", sub(&cargo_build, Any)),
+                ("test", sub(&cargo_test, Any)),
+                ("bench", sub(&cargo_test, Any)),
+                ("clippy", sub(&cargo_build, None)),
+                (
+                    "doc",
+                    sub(
+                        &[
+                            ("--open", "open the docs in a browser"),
+                            ("--no-deps", "skip dependencies"),
+                            ("--document-private-items", "include private items"),
+                            ("-p", "select package (value)"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "clean",
+                    sub(
+                        &[
+                            ("--release", "only release artifacts"),
+                            ("--doc", "only documentation"),
+                            ("-p", "select package (value)"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "new",
+                    sub(
+                        &[
+                            ("--lib", "library package"),
+                            ("--bin", "binary package"),
+                            ("--name", "package name (value)"),
+                            ("--edition", "Rust edition (value)"),
+                            ("--vcs", "version control (value)"),
+                        ],
+                        Dir,
+                    ),
+                ),
+                (
+                    "init",
+                    sub(
+                        &[
+                            ("--lib", "library package"),
+                            ("--bin", "binary package"),
+                            ("--name", "package name (value)"),
+                            ("--edition", "Rust edition (value)"),
+                        ],
+                        Dir,
+                    ),
+                ),
+                (
+                    "add",
+                    sub(
+                        &[
+                            ("--dev", "development dependency"),
+                            ("--build", "build dependency"),
+                            ("--features", "features to enable (value)"),
+                            ("--optional", "optional dependency"),
+                            ("--path", "local path dependency (value)"),
+                            ("--git", "git dependency (value)"),
+                        ],
+                        Any,
+                    ),
+                ),
+                (
+                    "remove",
+                    sub(
+                        &[
+                            ("--dev", "development dependency"),
+                            ("--build", "build dependency"),
+                        ],
+                        Any,
+                    ),
+                ),
+                (
+                    "update",
+                    sub(
+                        &[
+                            ("-p", "update one package (value)"),
+                            ("--dry-run", "dry run"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "install",
+                    sub(
+                        &[
+                            ("--path", "install from a local path (value)"),
+                            ("--git", "install from git (value)"),
+                            ("--locked", "use the package's Cargo.lock"),
+                            ("--force", "overwrite installed binaries"),
+                            ("--root", "install root (value)"),
+                        ],
+                        Any,
+                    ),
+                ),
+                (
+                    "fmt",
+                    sub(
+                        &[
+                            ("--all", "all packages"),
+                            ("--check", "only check formatting"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "fix",
+                    sub(
+                        &[
+                            ("--edition", "migrate to the next edition"),
+                            ("--allow-dirty", "fix with uncommitted changes"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "tree",
+                    sub(
+                        &[
+                            ("-i", "invert, showing dependents (value)"),
+                            ("-e", "edge kinds (value)"),
+                            ("--depth", "maximum depth (value)"),
+                            ("-d", "show duplicate dependencies"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "publish",
+                    sub(
+                        &[
+                            ("--dry-run", "dry run"),
+                            ("--allow-dirty", "publish with uncommitted changes"),
+                        ],
+                        None,
+                    ),
+                ),
+            ]),
+        });
+
+        let docker_images = Dynamic(DOCKER_IMAGES);
+        let docker_containers = Dynamic(DOCKER_CONTAINERS);
+        let docker_run = [
+            ("-d", "run in the background"),
+            ("--detach", "run in the background"),
+            ("-i", "keep stdin open"),
+            ("-t", "allocate a terminal"),
+            ("-p", "publish a port (value)"),
+            ("--publish", "publish a port (value)"),
+            ("-v", "mount a volume (value)"),
+            ("--volume", "mount a volume (value)"),
+            ("-e", "set an environment variable (value)"),
+            ("--env", "set an environment variable (value)"),
+            ("--name", "container name (value)"),
+            ("--rm", "remove the container on exit"),
+            ("--network", "connect to a network (value)"),
+            ("-w", "working directory (value)"),
+            ("--entrypoint", "override the entrypoint (value)"),
+        ];
+        add(CommandSpec {
+            name: "docker".into(),
+            subcommands: vec![
+                ("run", "Create and run a container"),
+                ("build", "Build an image"),
+                ("ps", "List containers"),
+                ("images", "List images"),
+                ("pull", "Download an image"),
+                ("push", "Upload an image"),
+                ("exec", "Run a command in a running container"),
+                ("logs", "Show container logs"),
+                ("start", "Start stopped containers"),
+                ("stop", "Stop running containers"),
+                ("restart", "Restart containers"),
+                ("kill", "Kill running containers"),
+                ("rm", "Remove containers"),
+                ("rmi", "Remove images"),
+                ("inspect", "Show low-level object details"),
+                ("tag", "Tag an image"),
+                ("cp", "Copy files to or from a container"),
+                ("compose", "Manage multi-container applications"),
+                ("network", "Manage networks"),
+                ("volume", "Manage volumes"),
+                ("system", "Manage Docker"),
+                ("login", "Log in to a registry"),
+                ("logout", "Log out from a registry"),
+            ],
+            flags: vec![
+                ("--help", "help"),
+                ("-v", "print the version"),
+                ("--version", "print the version"),
+                ("-H", "daemon socket (value)"),
+                ("--context", "context to use (value)"),
+            ],
+            default_arg: None,
+            flag_value_kind: HashMap::new(),
+            subcommand_specs: HashMap::from_iter([
+                ("run", sub(&docker_run, docker_images)),
+                (
+                    "build",
+                    sub(
+                        &[
+                            ("-t", "image name and tag (value)"),
+                            ("--tag", "image name and tag (value)"),
+                            ("-f", "Dockerfile (value)"),
+                            ("--file", "Dockerfile (value)"),
+                            ("--no-cache", "do not use the cache"),
+                            ("--pull", "pull newer base images"),
+                            ("--build-arg", "build-time variable (value)"),
+                            ("--target", "stage to build (value)"),
+                            ("--platform", "target platform (value)"),
+                        ],
+                        Dir,
+                    ),
+                ),
+                (
+                    "ps",
+                    sub(
+                        &[
+                            ("-a", "include stopped containers"),
+                            ("-q", "only IDs"),
+                            ("-s", "show sizes"),
+                            ("-f", "filter (value)"),
+                            ("--format", "output template (value)"),
+                        ],
+                        None,
+                    ),
+                ),
+                (
+                    "images",
+                    sub(
+                        &[
+                            ("-a", "include intermediate images"),
+                            ("-q", "only IDs"),
+                            ("--format", "output template (value)"),
+                        ],
+                        docker_images,
+                    ),
+                ),
+                (
+                    "pull",
+                    sub(&[("-a", "all tags"), ("-q", "quiet")], docker_images),
+                ),
+                (
+                    "push",
+                    sub(&[("-a", "all tags"), ("-q", "quiet")], docker_images),
+                ),
+                (
+                    "exec",
+                    sub(
+                        &[
+                            ("-i", "keep stdin open"),
+                            ("-t", "allocate a terminal"),
+                            ("-d", "run in the background"),
+                            ("-e", "set an environment variable (value)"),
+                            ("-u", "user (value)"),
+                            ("-w", "working directory (value)"),
+                        ],
+                        docker_containers,
+                    ),
+                ),
+                (
+                    "logs",
+                    sub(
+                        &[
+                            ("-f", "follow the output"),
+                            ("--follow", "follow the output"),
+                            ("--tail", "lines from the end (value)"),
+                            ("-t", "show timestamps"),
+                            ("--since", "logs since (value)"),
+                        ],
+                        docker_containers,
+                    ),
+                ),
+                ("start", sub(&[("-a", "attach output")], docker_containers)),
+                (
+                    "stop",
+                    sub(&[("-t", "seconds to wait (value)")], docker_containers),
+                ),
+                (
+                    "restart",
+                    sub(&[("-t", "seconds to wait (value)")], docker_containers),
+                ),
+                (
+                    "kill",
+                    sub(&[("-s", "signal to send (value)")], docker_containers),
+                ),
+                (
+                    "rm",
+                    sub(
+                        &[
+                            ("-f", "remove running containers"),
+                            ("-v", "remove anonymous volumes"),
+                        ],
+                        docker_containers,
+                    ),
+                ),
+                (
+                    "rmi",
+                    sub(
+                        &[("-f", "force"), ("--no-prune", "keep untagged parents")],
+                        docker_images,
+                    ),
+                ),
+                (
+                    "inspect",
+                    sub(
+                        &[
+                            ("-f", "output template (value)"),
+                            ("--format", "output template (value)"),
+                            ("--type", "object type (value)"),
+                        ],
+                        docker_containers,
+                    ),
+                ),
+                ("tag", sub(&[], docker_images)),
+                ("cp", sub(&[("-a", "archive mode")], Path)),
+                (
+                    "login",
+                    sub(
+                        &[
+                            ("-u", "username (value)"),
+                            ("-p", "password (value)"),
+                            ("--password-stdin", "read the password from stdin"),
+                        ],
+                        None,
+                    ),
+                ),
+            ]),
         });
     }
 
@@ -307,6 +1128,12 @@ impl NexusCompleter {
         }
     }
 
+    /// Ask the completers plugins register in `plugins` for the arguments
+    /// of their commands, ahead of flags, subcommands and files
+    pub fn set_plugins(&mut self, plugins: PluginCommandRegistry) {
+        self.plugins = plugins;
+    }
+
     /// Complete input with suggestions
     pub fn complete(&mut self, input: &str, pos: usize) -> Vec<CompletionResult> {
         let text = &input[..pos];
@@ -326,12 +1153,27 @@ impl NexusCompleter {
         // 1) 以降のトークン: コンテキストを見て決定
         let command = parts.first().copied().unwrap_or("");
         let spec_owned = self.get_or_discover_spec_owned(command);
+        // 入力済みのサブコマンド（`git commit -` など）
+        let sub_owned = spec_owned.as_ref().and_then(|spec| {
+            let typed = parts
+                .get(1)
+                .filter(|_| parts.len() > 2 || ends_with_space)?;
+            spec.subcommand_specs.get(*typed).cloned()
+        });
 
         // 1-a) 環境変数（$で始まる）
         if let Some(stripped) = current.strip_prefix('$') {
             return self.complete_env(stripped);
         }
 
+        // プラグインの補完（候補がなければ通常の補完へ）
+        if let Some(completer) = self.plugins.completer(command) {
+            let results = self.complete_from_plugin(&completer, input, pos, current);
+            if !results.is_empty() {
+                return results;
+            }
+        }
+
         // 1-b) フラグ（-で始まる）
         if current.starts_with('-') {
             // used_flags 抽出のため、command+これまでの引数を連結した部分を渡す
@@ -340,11 +1182,16 @@ impl NexusCompleter {
             } else {
                 &text[..text.len().saturating_sub(current.len())]
             };
-            return self.complete_flags(before_current, current, spec_owned.as_ref());
+            let flags = match (&sub_owned, &spec_owned) {
+                (Some(sub), _) => sub.flags.as_slice(),
+                (None, Some(spec)) => spec.flags.as_slice(),
+                (None, None) => &[],
+            };
+            return self.complete_flags(before_current, current, flags);
         }
 
         // 1-c) サブコマンド（2番目のトークン）
-        if parts.len() == 2 && !ends_with_space {
+        if (parts.len() == 2 && !ends_with_space) || (parts.len() == 1 && ends_with_space) {
             if let Some(spec) = spec_owned.as_ref() {
                 if !spec.subcommands.is_empty() {
                     return self.complete_subcommand(spec, current);
@@ -360,7 +1207,8 @@ impl NexusCompleter {
                     return self.complete_by_kind(*kind, current);
                 }
             }
-            return self.complete_by_kind(spec.default_arg, current);
+            let kind = sub_owned.map_or(spec.default_arg, |sub| sub.arg);
+            return self.complete_by_kind(kind, current);
         }
 
         // それ以外はファイル/ディレクトリ
@@ -505,7 +1353,7 @@ impl NexusCompleter {
         &self,
         command: &str,
         current: &str,
-        spec: Option<&CommandSpec>,
+        flags: &[(&'static str, &'static str)],
     ) -> Vec<CompletionResult> {
         let mut out = Vec::new();
 
@@ -530,10 +1378,8 @@ impl NexusCompleter {
             }
         };
 
-        if let Some(spec) = spec {
-            for (flag, desc) in &spec.flags {
-                push_flag(flag, desc, &mut out);
-            }
+        for (flag, desc) in flags {
+            push_flag(flag, desc, &mut out);
         }
         for &(flag, desc) in [
             ("-h", "help"),
@@ -709,6 +1555,7 @@ impl NexusCompleter {
             flags,
             default_arg,
             flag_value_kind,
+            subcommand_specs: HashMap::new(),
         })
     }
 
@@ -729,16 +1576,94 @@ impl NexusCompleter {
         out
     }
 
-    fn complete_by_kind(&self, kind: ArgKind, current: &str) -> Vec<CompletionResult> {
+    fn complete_by_kind(&mut self, kind: ArgKind, current: &str) -> Vec<CompletionResult> {
         match kind {
-            ArgKind::Path | ArgKind::File | ArgKind::Dir | ArgKind::Any => {
-                self.complete_file(current)
+            ArgKind::Path | ArgKind::File | ArgKind::Any => self.complete_file(current),
+            ArgKind::Dir => {
+                let mut out = self.complete_file(current);
+                out.retain(|c| c.completion_type == CompletionType::Directory);
+                out
             }
             ArgKind::Env => self.complete_env(current),
+            ArgKind::Variable => self.complete_variable(current),
+            ArgKind::Command => self.complete_command(current),
+            ArgKind::Dynamic(argv) => self.complete_dynamic(argv, current),
             ArgKind::None => Vec::new(),
         }
     }
 
+    fn complete_variable(&self, prefix: &str) -> Vec<CompletionResult> {
+        let mut out: Vec<CompletionResult> = self
+            .variable_cache
+            .iter()
+            .filter(|var| var.starts_with(prefix))
+            .map(|var| CompletionResult {
+                completion: var.clone(),
+                display: Some(format!("{:<20} variable", var)),
+                completion_type: CompletionType::Variable,
+                score: self.calculate_score(prefix, var),
+            })
+            .collect();
+        out.sort_by(|a, b| b.score.cmp(&a.score));
+        out.truncate(self.completion_config.max_suggestions);
+        out
+    }
+
+    /// Candidates printed one per line by a command such as `git remote`;
+    /// nothing if the command is missing or fails
+    fn complete_dynamic(&self, argv: &[&str], current: &str) -> Vec<CompletionResult> {
+        let Some((program, args)) = argv.split_first() else {
+            return Vec::new();
+        };
+        let output = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            _ => return Vec::new(),
+        };
+        let mut out: Vec<CompletionResult> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|item| !item.is_empty() && item.starts_with(current))
+            .map(|item| CompletionResult {
+                completion: item.to_string(),
+                display: Some(format!("{:<20} {}", item, argv.join(" "))),
+                completion_type: CompletionType::Subcommand,
+                score: self.calculate_score(current, item),
+            })
+            .collect();
+        out.sort_by(|a, b| b.score.cmp(&a.score));
+        out.truncate(self.completion_config.max_suggestions);
+        out
+    }
+
+    /// Candidates a plugin's completer gives for `current`, the word under
+    /// the cursor at `pos` in `input`
+    fn complete_from_plugin(
+        &self,
+        completer: &PluginCompleter,
+        input: &str,
+        pos: usize,
+        current: &str,
+    ) -> Vec<CompletionResult> {
+        let mut out: Vec<CompletionResult> = (completer.complete)(input, pos)
+            .into_iter()
+            .filter(|item| item.starts_with(current))
+            .map(|item| CompletionResult {
+                display: Some(format!("{:<20} {}", item, completer.plugin)),
+                completion_type: CompletionType::Subcommand,
+                score: self.calculate_score(current, &item),
+                completion: item,
+            })
+            .collect();
+        out.sort_by(|a, b| b.score.cmp(&a.score));
+        out.truncate(self.completion_config.max_suggestions);
+        out
+    }
+
     /// Calculate completion score
     fn calculate_score(&self, input: &str, candidate: &str) -> i64 {
         if candidate.starts_with(input) {
@@ -823,4 +1748,99 @@ mod tests {
         let score = completer.fuzzy_score("lst", "list");
         assert!(score > 0);
     }
+
+    fn completions(completer: &mut NexusCompleter, line: &str) -> Vec<String> {
+        completer
+            .complete(line, line.len())
+            .into_iter()
+            .map(|c| c.completion)
+            .collect()
+    }
+
+    #[test]
+    fn test_argument_kinds_from_usage() {
+        assert_eq!(ArgKind::from_usage("cd [DIRECTORY]"), ArgKind::Dir);
+        assert_eq!(ArgKind::from_usage("help [COMMAND]"), ArgKind::Command);
+        assert_eq!(
+            ArgKind::from_usage("grep [OPTIONS] PATTERN [FILE...]"),
+            ArgKind::File
+        );
+        assert_eq!(
+            ArgKind::from_usage("env [OPTIONS] [COMMAND [ARGS]]"),
+            ArgKind::Command
+        );
+        assert_eq!(
+            ArgKind::from_usage("unset [OPTIONS] [NAME...]"),
+            ArgKind::Variable
+        );
+        assert_eq!(ArgKind::from_usage("unalias [NAME...]"), ArgKind::Any);
+        assert_eq!(ArgKind::from_usage("ps [OPTIONS]"), ArgKind::None);
+    }
+
+    #[test]
+    fn test_registered_builtin_flags() {
+        let mut completer = NexusCompleter::new();
+        completer.register_builtin(
+            "history",
+            "Command history management",
+            "history [OPTIONS]",
+            &[("-c".to_string(), "clear the history".to_string())],
+        );
+        assert_eq!(
+            completer.builtin_cache.get("history").map(String::as_str),
+            Some("Command history management")
+        );
+        let results = completer.complete("history -", 9);
+        let clear = results.iter().find(|c| c.completion == "-c").unwrap();
+        assert_eq!(clear.completion_type, CompletionType::Flag);
+        assert!(clear
+            .display
+            .as_deref()
+            .unwrap()
+            .contains("clear the history"));
+        // `history [OPTIONS]` takes no arguments to complete
+        assert!(completions(&mut completer, "history ").is_empty());
+    }
+
+    #[test]
+    fn test_subcommands_and_their_flags() {
+        let mut completer = NexusCompleter::new();
+        assert!(completions(&mut completer, "git ").contains(&"commit".to_string()));
+        assert_eq!(completions(&mut completer, "git comm"), ["commit"]);
+
+        let commit = completions(&mut completer, "git commit --");
+        assert!(commit.contains(&"--amend".to_string()));
+        assert!(!commit.contains(&"--version".to_string()));
+
+        assert!(completions(&mut completer, "cargo test --").contains(&"--no-run".to_string()));
+        assert!(completions(&mut completer, "docker run --").contains(&"--rm".to_string()));
+        assert!(completions(&mut completer, "docker ").contains(&"exec".to_string()));
+    }
+
+    #[test]
+    fn test_plugin_completers() {
+        let plugins = PluginCommandRegistry::default();
+        plugins.register_completer(PluginCompleter {
+            command: "kubectl".to_string(),
+            plugin: "kube@1.0.0".to_string(),
+            complete: std::sync::Arc::new(|line: &str, cursor: usize| {
+                assert_eq!(cursor, line.len());
+                vec!["web-1".to_string(), "web-2".to_string(), "db-0".to_string()]
+            }),
+        });
+        let mut completer = NexusCompleter::new();
+        completer.set_plugins(plugins.clone());
+        assert_eq!(
+            completions(&mut completer, "kubectl logs web"),
+            ["web-1", "web-2"]
+        );
+        let results = completer.complete("kubectl logs d", 14);
+        assert_eq!(
+            results[0].display.as_deref(),
+            Some("db-0                 kube@1.0.0")
+        );
+
+        plugins.unregister_plugin("kube@1.0.0");
+        assert!(!completions(&mut completer, "kubectl logs web").contains(&"web-1".to_string()));
+    }
 }
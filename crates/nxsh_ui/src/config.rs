@@ -232,6 +232,7 @@ impl Default for ThemeConfig {
 
 /// UI-specific configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UiConfig {
     pub show_status_bar: bool,
     pub show_header: bool,
@@ -249,6 +250,32 @@ pub struct UiConfig {
     pub auto_scroll_output: bool,
     pub scroll_buffer_size: usize,
     pub theme_name: String,
+    /// History file; `~/.nxsh_history` when unset
+    pub history_file: Option<PathBuf>,
+    /// Most commands kept in the history file
+    pub history_max_entries: usize,
+    /// Commands older than this many days are dropped from the history file
+    pub history_max_age_days: Option<u64>,
+    /// Number of appended history entries written between fsyncs
+    pub history_sync_batch: usize,
+    /// Line editing keymap, `emacs` or `vi`
+    pub edit_mode: String,
+    /// Extra bindings from key (`Ctrl+K`, `Alt+f`) to readline command name
+    pub keybindings: HashMap<String, String>,
+    /// Extra bindings for Vi command mode
+    pub vi_command_keybindings: HashMap<String, String>,
+    /// Prompt template, see `prompt::PromptTemplate`
+    pub prompt_template: String,
+    /// Template drawn right-aligned on the input line; empty for none
+    pub right_prompt_template: String,
+    /// Milliseconds git and other slow prompt segments may take
+    pub prompt_segment_timeout_ms: u64,
+    /// Prompt accepted lines are redrawn with; empty keeps the full prompt
+    pub transient_prompt_template: String,
+    /// Print the exit status and run time of failed or slow commands
+    pub show_command_status: bool,
+    /// Commands running at least this many milliseconds count as slow
+    pub command_status_threshold_ms: u64,
 }
 
 impl Default for UiConfig {
@@ -270,6 +297,19 @@ impl Default for UiConfig {
             auto_scroll_output: true,
             scroll_buffer_size: 1000,
             theme_name: "default".to_string(),
+            history_file: None,
+            history_max_entries: 10000,
+            history_max_age_days: None,
+            history_sync_batch: 8,
+            edit_mode: "emacs".to_string(),
+            keybindings: HashMap::new(),
+            vi_command_keybindings: HashMap::new(),
+            prompt_template: crate::prompt::DEFAULT_PROMPT_TEMPLATE.to_string(),
+            right_prompt_template: crate::prompt::DEFAULT_RIGHT_PROMPT_TEMPLATE.to_string(),
+            prompt_segment_timeout_ms: 150,
+            transient_prompt_template: crate::prompt::DEFAULT_TRANSIENT_PROMPT_TEMPLATE.to_string(),
+            show_command_status: true,
+            command_status_threshold_ms: 5000,
         }
     }
 }
@@ -0,0 +1,200 @@
+//! Asking the user for permission
+//!
+//! [`ask`] shows who is asking with a summary of each permission it wants,
+//! then asks about the permissions one at a time: `y` grants one, `n` or
+//! Enter denies it, `a` grants it and the rest, and `q`, Esc or Ctrl-C deny
+//! it and the rest. Plugins ask this way for the capabilities their
+//! manifest lists before they are loaded. [`confirm`] asks a single
+//! question the same way, as `rm -i` and `cp -i` do before each file.
+
+use crate::input_handler::KeyEvent;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use std::io::{self, IsTerminal, Write};
+
+/// A permission being asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permission {
+    /// Name it is recorded under
+    pub name: String,
+    /// What it allows, in words
+    pub summary: String,
+}
+
+/// Permissions something asks for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsentRequest {
+    /// Who is asking, such as `plugin greeter 1.0.0`
+    pub requester: String,
+    /// What the requester is, shown after it; empty for nothing
+    pub description: String,
+    pub permissions: Vec<Permission>,
+}
+
+/// How the user answered about a permission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// Yes to this and the remaining permissions
+    YesToAll,
+    /// No to this and the remaining permissions
+    NoToAll,
+}
+
+/// The answer `key` gives, if it is one
+pub fn answer_for(key: KeyEvent) -> Option<Answer> {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Answer::NoToAll)
+        }
+        KeyCode::Char('y' | 'Y') => Some(Answer::Yes),
+        KeyCode::Char('n' | 'N') | KeyCode::Enter => Some(Answer::No),
+        KeyCode::Char('a' | 'A') => Some(Answer::YesToAll),
+        KeyCode::Char('q' | 'Q') | KeyCode::Esc => Some(Answer::NoToAll),
+        _ => None,
+    }
+}
+
+/// The summary shown before the questions
+pub fn summary(request: &ConsentRequest) -> String {
+    let mut text = if request.description.is_empty() {
+        format!("{} asks for permission to:\n", request.requester)
+    } else {
+        format!(
+            "{} ({}) asks for permission to:\n",
+            request.requester, request.description
+        )
+    };
+    let width = request
+        .permissions
+        .iter()
+        .map(|permission| permission.name.len())
+        .max()
+        .unwrap_or(0);
+    for permission in &request.permissions {
+        text.push_str(&format!(
+            "  {:width$}  {}\n",
+            permission.name, permission.summary
+        ));
+    }
+    text
+}
+
+/// Ask the user about each permission in `request` on the terminal,
+/// writing to `out`, and return whether each is granted, or `None` when
+/// standard input is not a terminal to answer on
+pub fn ask<W: Write + ?Sized>(
+    request: &ConsentRequest,
+    out: &mut W,
+) -> io::Result<Option<Vec<bool>>> {
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    out.write_all(summary(request).as_bytes())?;
+    let mut granted = Vec::with_capacity(request.permissions.len());
+    for permission in &request.permissions {
+        write!(out, "Allow it to {}? [y/N/a/q] ", permission.summary)?;
+        out.flush()?;
+        let answer = read_answer()?;
+        let yes = matches!(answer, Answer::Yes | Answer::YesToAll);
+        writeln!(out, "{}", if yes { "yes" } else { "no" })?;
+        match answer {
+            Answer::Yes | Answer::No => granted.push(yes),
+            Answer::YesToAll | Answer::NoToAll => {
+                granted.resize(request.permissions.len(), yes);
+                break;
+            }
+        }
+    }
+    Ok(Some(granted))
+}
+
+/// Ask `question` on the terminal, writing to `out`, and return the
+/// answer, or `None` when standard input is not a terminal to answer on
+pub fn confirm<W: Write + ?Sized>(question: &str, out: &mut W) -> io::Result<Option<Answer>> {
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    write!(out, "{question}? [y/N/a/q] ")?;
+    out.flush()?;
+    let answer = read_answer()?;
+    let yes = matches!(answer, Answer::Yes | Answer::YesToAll);
+    writeln!(out, "{}", if yes { "yes" } else { "no" })?;
+    Ok(Some(answer))
+}
+
+/// Read keys until one answers
+fn read_answer() -> io::Result<Answer> {
+    struct RawModeGuard;
+    impl Drop for RawModeGuard {
+        fn drop(&mut self) {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+
+    terminal::enable_raw_mode()?;
+    let _guard = RawModeGuard;
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if let Some(answer) = answer_for(key.into()) {
+                return Ok(answer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    #[test]
+    fn maps_keys_to_answers() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(answer_for(key(KeyCode::Char('y'), none)), Some(Answer::Yes));
+        assert_eq!(answer_for(key(KeyCode::Enter, none)), Some(Answer::No));
+        assert_eq!(
+            answer_for(key(KeyCode::Char('a'), none)),
+            Some(Answer::YesToAll)
+        );
+        assert_eq!(answer_for(key(KeyCode::Esc, none)), Some(Answer::NoToAll));
+        assert_eq!(
+            answer_for(key(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Answer::NoToAll)
+        );
+        assert_eq!(answer_for(key(KeyCode::Char('x'), none)), None);
+    }
+
+    #[test]
+    fn summarizes_the_permissions() {
+        let request = ConsentRequest {
+            requester: "plugin greeter 1.0.0".to_string(),
+            description: "Greets people".to_string(),
+            permissions: vec![
+                Permission {
+                    name: "env_read".to_string(),
+                    summary: "read environment variables".to_string(),
+                },
+                Permission {
+                    name: "network_request".to_string(),
+                    summary: "connect to the network".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            summary(&request),
+            "plugin greeter 1.0.0 (Greets people) asks for permission to:\n\
+             \x20 env_read         read environment variables\n\
+             \x20 network_request  connect to the network\n"
+        );
+    }
+}
@@ -0,0 +1,714 @@
+//! Line editing operations behind the Emacs and Vi keymaps
+//!
+//! [`EditState`] applies an [`InputAction`] to a line and cursor owned by
+//! the caller. It keeps what editing needs across keystrokes: the kill ring
+//! shared by Emacs kills and Vi deletes and yanks, and an undo history for
+//! the line being edited. Cursor positions are byte offsets on char
+//! boundaries.
+
+use crate::input_handler::{InputAction, ViInsertPosition, ViMotion, ViOperator};
+use std::ops::Range;
+
+/// Most kills remembered for `yank-pop`
+const KILL_RING_SIZE: usize = 16;
+
+/// What the previous action did, for actions that continue it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LastEdit {
+    None,
+    Insert,
+    Kill,
+    /// Text just yanked, so `yank-pop` can replace it
+    Yank(usize, usize),
+}
+
+/// Kill ring, undo history and the state of the previous edit
+#[derive(Debug, Clone)]
+pub struct EditState {
+    kill_ring: Vec<String>,
+    yank_index: usize,
+    undo: Vec<(String, usize)>,
+    last: LastEdit,
+}
+
+impl Default for EditState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EditState {
+    pub fn new() -> Self {
+        Self {
+            kill_ring: Vec::new(),
+            yank_index: 0,
+            undo: Vec::new(),
+            last: LastEdit::None,
+        }
+    }
+
+    /// Start editing a new line; the kill ring carries over
+    pub fn reset(&mut self) {
+        self.undo.clear();
+        self.last = LastEdit::None;
+    }
+
+    /// Most recent kill
+    pub fn last_kill(&self) -> Option<&str> {
+        self.kill_ring.last().map(String::as_str)
+    }
+
+    /// Apply an editing action. Returns `false` for actions that are not
+    /// line edits (submit, history, completion, ...) so the caller handles them.
+    pub fn apply(&mut self, action: &InputAction, line: &mut String, cursor: &mut usize) -> bool {
+        *cursor = floor_boundary(line, (*cursor).min(line.len()));
+        let before = (line.clone(), *cursor);
+        let mut last = LastEdit::None;
+
+        match action {
+            InputAction::InsertChar(c) => {
+                line.insert(*cursor, *c);
+                *cursor += c.len_utf8();
+                last = LastEdit::Insert;
+            }
+            InputAction::Backspace => {
+                let start = prev_char(line, *cursor);
+                line.drain(start..*cursor);
+                *cursor = start;
+            }
+            InputAction::Delete => {
+                let end = next_char(line, *cursor);
+                line.drain(*cursor..end);
+            }
+            InputAction::MoveLeft => *cursor = prev_char(line, *cursor),
+            InputAction::MoveRight => *cursor = next_char(line, *cursor),
+            InputAction::MoveWordLeft => *cursor = emacs_word_start(line, *cursor),
+            InputAction::MoveWordRight => *cursor = emacs_word_end(line, *cursor),
+            InputAction::MoveToStart => *cursor = 0,
+            InputAction::MoveToEnd => *cursor = line.len(),
+            InputAction::DeleteWord => {
+                let start = blank_word_start(line, *cursor);
+                self.kill(line, start..*cursor, cursor, true);
+                last = LastEdit::Kill;
+            }
+            InputAction::KillWordForward => {
+                let end = emacs_word_end(line, *cursor);
+                self.kill(line, *cursor..end, cursor, false);
+                last = LastEdit::Kill;
+            }
+            InputAction::DeleteToEnd => {
+                self.kill(line, *cursor..line.len(), cursor, false);
+                last = LastEdit::Kill;
+            }
+            InputAction::DeleteToStart => {
+                self.kill(line, 0..*cursor, cursor, true);
+                last = LastEdit::Kill;
+            }
+            InputAction::DeleteLine => {
+                self.kill(line, 0..line.len(), cursor, false);
+                last = LastEdit::Kill;
+            }
+            InputAction::Yank | InputAction::Paste => {
+                if let Some(text) = self.kill_ring.last().cloned() {
+                    self.yank_index = self.kill_ring.len() - 1;
+                    line.insert_str(*cursor, &text);
+                    last = LastEdit::Yank(*cursor, *cursor + text.len());
+                    *cursor += text.len();
+                }
+            }
+            InputAction::YankPop => {
+                let LastEdit::Yank(start, end) = self.last else {
+                    return true;
+                };
+                self.yank_index = self
+                    .yank_index
+                    .checked_sub(1)
+                    .unwrap_or(self.kill_ring.len() - 1);
+                let text = self.kill_ring[self.yank_index].clone();
+                line.replace_range(start..end, &text);
+                *cursor = start + text.len();
+                last = LastEdit::Yank(start, *cursor);
+            }
+            InputAction::TransposeChars => transpose_chars(line, cursor),
+            InputAction::TransposeWords => transpose_words(line, cursor),
+            InputAction::Undo => {
+                if let Some((text, position)) = self.undo.pop() {
+                    *line = text;
+                    *cursor = position;
+                }
+                self.last = LastEdit::None;
+                return true;
+            }
+            InputAction::ViCommandMode => {
+                // Leaving insert mode steps back onto the last inserted char
+                *cursor = prev_char(line, *cursor);
+            }
+            InputAction::ViInsert(position) => {
+                *cursor = match position {
+                    ViInsertPosition::Cursor => *cursor,
+                    ViInsertPosition::After => next_char(line, *cursor),
+                    ViInsertPosition::LineStart => first_non_blank(line),
+                    ViInsertPosition::LineEnd => line.len(),
+                };
+            }
+            InputAction::ViMove { motion, count } => {
+                if let Some(range) = motion_range(line, *cursor, motion, *count, None) {
+                    *cursor = if range.start < *cursor || range.end <= *cursor {
+                        range.start
+                    } else if motion.is_inclusive() {
+                        prev_char(line, range.end)
+                    } else {
+                        range.end
+                    };
+                }
+                clamp_normal(line, cursor);
+            }
+            InputAction::ViOperate {
+                operator,
+                motion,
+                count,
+            } => {
+                let Some(range) = motion_range(line, *cursor, motion, *count, Some(*operator))
+                else {
+                    return true;
+                };
+                self.push_kill(line[range.clone()].to_string());
+                match operator {
+                    ViOperator::Yank => *cursor = range.start.min(*cursor),
+                    ViOperator::Delete => {
+                        line.drain(range.clone());
+                        *cursor = range.start;
+                        clamp_normal(line, cursor);
+                    }
+                    ViOperator::Change => {
+                        line.drain(range.clone());
+                        *cursor = range.start;
+                    }
+                }
+            }
+            InputAction::ViPut { before, count } => {
+                if let Some(text) = self.kill_ring.last() {
+                    let at = if *before {
+                        *cursor
+                    } else {
+                        next_char(line, *cursor)
+                    };
+                    let text = text.repeat((*count).max(1));
+                    line.insert_str(at, &text);
+                    *cursor = prev_char(line, at + text.len());
+                }
+            }
+            InputAction::ViReplaceChar { replacement, count } => {
+                let end = (0..(*count).max(1)).try_fold(*cursor, |at, _| {
+                    (at < line.len()).then(|| next_char(line, at))
+                });
+                // Like vi, nothing changes when there are fewer chars than the count
+                if let Some(end) = end {
+                    let text = replacement.to_string().repeat((*count).max(1));
+                    line.replace_range(*cursor..end, &text);
+                    *cursor = prev_char(line, *cursor + text.len());
+                }
+            }
+            _ => return false,
+        }
+
+        let changed = *line != before.0;
+        let extends_insert = last == LastEdit::Insert && self.last == LastEdit::Insert;
+        if changed && !extends_insert {
+            self.undo.push(before);
+        }
+        self.last = last;
+        true
+    }
+
+    /// Remove `range` into the kill ring. Consecutive kills build one entry,
+    /// with backward kills prepended.
+    fn kill(&mut self, line: &mut String, range: Range<usize>, cursor: &mut usize, backward: bool) {
+        if range.is_empty() {
+            return;
+        }
+        let text: String = line.drain(range.clone()).collect();
+        *cursor = range.start;
+        match self.kill_ring.last_mut() {
+            Some(last) if self.last == LastEdit::Kill => {
+                if backward {
+                    last.insert_str(0, &text);
+                } else {
+                    last.push_str(&text);
+                }
+            }
+            _ => self.push_kill(text),
+        }
+    }
+
+    fn push_kill(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.kill_ring.push(text);
+        if self.kill_ring.len() > KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.yank_index = self.kill_ring.len() - 1;
+    }
+}
+
+/// Character classes for Vi word motions: `word` is a run of letters,
+/// digits and `_` or a run of other punctuation; `WORD` is any non-blank run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punct,
+}
+
+fn class(c: char, big: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Blank
+    } else if big || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+fn char_at(line: &str, at: usize) -> Option<char> {
+    line[at..].chars().next()
+}
+
+fn floor_boundary(line: &str, mut at: usize) -> usize {
+    while !line.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+fn prev_char(line: &str, at: usize) -> usize {
+    line[..at].char_indices().last().map_or(0, |(i, _)| i)
+}
+
+fn next_char(line: &str, at: usize) -> usize {
+    char_at(line, at).map_or(line.len(), |c| at + c.len_utf8())
+}
+
+/// In Vi normal mode the cursor sits on a char, never past the last one
+fn clamp_normal(line: &str, cursor: &mut usize) {
+    if *cursor >= line.len() {
+        *cursor = prev_char(line, line.len());
+    }
+}
+
+fn first_non_blank(line: &str) -> usize {
+    line.find(|c: char| !c.is_whitespace())
+        .unwrap_or(line.len())
+}
+
+/// Start of the alphanumeric word before the cursor (Emacs `backward-word`)
+fn emacs_word_start(line: &str, at: usize) -> usize {
+    let mut at = at;
+    while at > 0 && !is_emacs_word(char_before(line, at)) {
+        at = prev_char(line, at);
+    }
+    while at > 0 && is_emacs_word(char_before(line, at)) {
+        at = prev_char(line, at);
+    }
+    at
+}
+
+/// End of the alphanumeric word after the cursor (Emacs `forward-word`)
+fn emacs_word_end(line: &str, at: usize) -> usize {
+    let mut at = at;
+    while char_at(line, at).is_some_and(|c| !is_emacs_word(c)) {
+        at = next_char(line, at);
+    }
+    while char_at(line, at).is_some_and(is_emacs_word) {
+        at = next_char(line, at);
+    }
+    at
+}
+
+/// Start of the whitespace-delimited word before the cursor (`unix-word-rubout`)
+fn blank_word_start(line: &str, at: usize) -> usize {
+    let mut at = at;
+    while at > 0 && char_before(line, at).is_whitespace() {
+        at = prev_char(line, at);
+    }
+    while at > 0 && !char_before(line, at).is_whitespace() {
+        at = prev_char(line, at);
+    }
+    at
+}
+
+fn is_emacs_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn char_before(line: &str, at: usize) -> char {
+    line[..at].chars().next_back().unwrap_or(' ')
+}
+
+/// `w`/`W`: start of the next word
+fn vi_word_forward(line: &str, at: usize, big: bool) -> usize {
+    let Some(c) = char_at(line, at) else {
+        return at;
+    };
+    let start_class = class(c, big);
+    let mut at = at;
+    while char_at(line, at).is_some_and(|c| class(c, big) == start_class)
+        && start_class != CharClass::Blank
+    {
+        at = next_char(line, at);
+    }
+    while char_at(line, at).is_some_and(|c| class(c, big) == CharClass::Blank) {
+        at = next_char(line, at);
+    }
+    at
+}
+
+/// `b`/`B`: start of the current or previous word
+fn vi_word_backward(line: &str, at: usize, big: bool) -> usize {
+    let mut at = at;
+    while at > 0 && class(char_before(line, at), big) == CharClass::Blank {
+        at = prev_char(line, at);
+    }
+    if at == 0 {
+        return 0;
+    }
+    let word_class = class(char_before(line, at), big);
+    while at > 0 && class(char_before(line, at), big) == word_class {
+        at = prev_char(line, at);
+    }
+    at
+}
+
+/// `e`/`E`: last char of the current or next word
+fn vi_word_end(line: &str, at: usize, big: bool) -> usize {
+    let mut at = next_char(line, at);
+    while char_at(line, at).is_some_and(|c| class(c, big) == CharClass::Blank) {
+        at = next_char(line, at);
+    }
+    let Some(c) = char_at(line, at) else {
+        return prev_char(line, line.len());
+    };
+    let word_class = class(c, big);
+    while char_at(line, next_char(line, at)).is_some_and(|c| class(c, big) == word_class) {
+        at = next_char(line, at);
+    }
+    at
+}
+
+/// `iw`/`aw`: the word (or run of blanks) under the cursor, and for `aw`
+/// the blanks after it, or before it when there are none after
+fn word_object(line: &str, at: usize, big: bool, around: bool) -> Option<Range<usize>> {
+    let object_class = class(char_at(line, at)?, big);
+    let mut start = at;
+    while start > 0 && class(char_before(line, start), big) == object_class {
+        start = prev_char(line, start);
+    }
+    let mut end = at;
+    while char_at(line, end).is_some_and(|c| class(c, big) == object_class) {
+        end = next_char(line, end);
+    }
+    if around && object_class != CharClass::Blank {
+        let mut trailing = end;
+        while char_at(line, trailing).is_some_and(char::is_whitespace) {
+            trailing = next_char(line, trailing);
+        }
+        if trailing > end {
+            end = trailing;
+        } else {
+            while start > 0 && char_before(line, start).is_whitespace() {
+                start = prev_char(line, start);
+            }
+        }
+    }
+    Some(start..end)
+}
+
+/// `f`/`F`/`t`/`T`: position of the count-th `target` char in one direction
+fn find_char(line: &str, at: usize, target: char, forward: bool, count: usize) -> Option<usize> {
+    let mut found = at;
+    for _ in 0..count {
+        found = if forward {
+            let from = next_char(line, found);
+            from + line[from..].find(target)?
+        } else {
+            line[..found].rfind(target)?
+        };
+    }
+    Some(found)
+}
+
+/// Text a motion covers from `at`, as a byte range. With an operator the
+/// range is what the operator acts on; `cw` on a word changes to its end.
+fn motion_range(
+    line: &str,
+    at: usize,
+    motion: &ViMotion,
+    count: usize,
+    operator: Option<ViOperator>,
+) -> Option<Range<usize>> {
+    let count = count.max(1);
+    let repeat = |step: &dyn Fn(usize) -> usize| (0..count).fold(at, |pos, _| step(pos));
+    let inclusive = |end: usize| at..next_char(line, end);
+    let range = match motion {
+        ViMotion::Left => repeat(&|pos| prev_char(line, pos))..at,
+        ViMotion::Right => at..repeat(&|pos| next_char(line, pos)),
+        ViMotion::WordForward { big }
+            if operator == Some(ViOperator::Change)
+                && char_at(line, at).is_some_and(|c| !c.is_whitespace()) =>
+        {
+            // `cw` on a word is `ce`, except that it stays within the word
+            // the cursor is on
+            let end = (1..count).fold(last_of_word(line, at, *big), |pos, _| {
+                vi_word_end(line, pos, *big)
+            });
+            inclusive(end)
+        }
+        ViMotion::WordForward { big } => at..repeat(&|pos| vi_word_forward(line, pos, *big)),
+        ViMotion::WordBackward { big } => repeat(&|pos| vi_word_backward(line, pos, *big))..at,
+        ViMotion::WordEnd { big } => inclusive(repeat(&|pos| vi_word_end(line, pos, *big))),
+        ViMotion::LineStart => 0..at,
+        ViMotion::FirstNonBlank => {
+            let first = first_non_blank(line);
+            first.min(at)..first.max(at)
+        }
+        ViMotion::LineEnd => at..line.len(),
+        ViMotion::Find {
+            target,
+            forward,
+            till,
+        } => {
+            let found = find_char(line, at, *target, *forward, count)?;
+            match (forward, till) {
+                (true, false) => inclusive(found),
+                (true, true) => at..found,
+                (false, false) => found..at,
+                (false, true) => next_char(line, found)..at,
+            }
+        }
+        ViMotion::Word { big, around } => word_object(line, at, *big, *around)?,
+        ViMotion::WholeLine => 0..line.len(),
+    };
+    Some(range)
+}
+
+/// Last char of the word `at` is in
+fn last_of_word(line: &str, at: usize, big: bool) -> usize {
+    let word_class = class(char_at(line, at).unwrap_or(' '), big);
+    let mut at = at;
+    while char_at(line, next_char(line, at)).is_some_and(|c| class(c, big) == word_class) {
+        at = next_char(line, at);
+    }
+    at
+}
+
+/// Ctrl-T: swap the chars around the cursor, or the last two at the end
+fn transpose_chars(line: &mut String, cursor: &mut usize) {
+    if *cursor == 0 || line.chars().count() < 2 {
+        return;
+    }
+    let at = if *cursor >= line.len() {
+        prev_char(line, line.len())
+    } else {
+        *cursor
+    };
+    let start = prev_char(line, at);
+    let end = next_char(line, at);
+    let first: String = line[start..at].to_string();
+    let second: String = line[at..end].to_string();
+    line.replace_range(start..end, &format!("{second}{first}"));
+    *cursor = end;
+}
+
+/// Alt-T: swap the word before the cursor with the word after it
+fn transpose_words(line: &mut String, cursor: &mut usize) {
+    let words: Vec<Range<usize>> = {
+        let mut words = Vec::new();
+        let mut at = 0;
+        while at < line.len() {
+            let start = emacs_word_end(line, at);
+            let start = emacs_word_start(line, start);
+            let end = emacs_word_end(line, start);
+            if start >= end || start < at {
+                break;
+            }
+            words.push(start..end);
+            at = end;
+        }
+        words
+    };
+    // Swap the word after the cursor with the one before it; inside a word
+    // that word comes first, and past the last word the last two swap
+    let second = match words.iter().position(|w| w.end > *cursor) {
+        Some(i) if words[i].start < *cursor => i + 1,
+        Some(i) => i,
+        None => words.len().saturating_sub(1),
+    };
+    let second = second.min(words.len().saturating_sub(1));
+    if second == 0 {
+        return;
+    }
+    let (a, b) = (words[second - 1].clone(), words[second].clone());
+    let swapped = format!(
+        "{}{}{}",
+        &line[b.clone()],
+        &line[a.end..b.start],
+        &line[a.clone()]
+    );
+    line.replace_range(a.start..b.end, &swapped);
+    *cursor = b.end;
+}
+
+impl ViMotion {
+    /// Whether a move with this motion lands on the last char it covers
+    fn is_inclusive(&self) -> bool {
+        matches!(
+            self,
+            ViMotion::WordEnd { .. } | ViMotion::LineEnd | ViMotion::Find { forward: true, .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str, cursor: usize, actions: &[InputAction]) -> (String, usize) {
+        let mut state = EditState::new();
+        let mut line = line.to_string();
+        let mut cursor = cursor;
+        for action in actions {
+            state.apply(action, &mut line, &mut cursor);
+        }
+        (line, cursor)
+    }
+
+    fn operate(operator: ViOperator, motion: ViMotion) -> InputAction {
+        InputAction::ViOperate {
+            operator,
+            motion,
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn emacs_kills_collect_and_yank_pop_cycles() {
+        let mut state = EditState::new();
+        let mut line = "one two three".to_string();
+        let mut cursor = line.len();
+        state.apply(&InputAction::DeleteWord, &mut line, &mut cursor);
+        state.apply(&InputAction::DeleteWord, &mut line, &mut cursor);
+        assert_eq!(line, "one ");
+        assert_eq!(state.last_kill(), Some("two three"));
+
+        state.apply(&InputAction::MoveToStart, &mut line, &mut cursor);
+        state.apply(&InputAction::DeleteToEnd, &mut line, &mut cursor);
+        state.apply(&InputAction::Yank, &mut line, &mut cursor);
+        assert_eq!(line, "one ");
+        state.apply(&InputAction::YankPop, &mut line, &mut cursor);
+        assert_eq!(line, "two three");
+
+        state.apply(&InputAction::Undo, &mut line, &mut cursor);
+        assert_eq!(line, "one ");
+    }
+
+    #[test]
+    fn emacs_word_motion_and_transpose() {
+        assert_eq!(
+            run("ab", 1, &[InputAction::TransposeChars]),
+            ("ba".into(), 2)
+        );
+        assert_eq!(
+            run("abc", 3, &[InputAction::TransposeChars]),
+            ("acb".into(), 3)
+        );
+        assert_eq!(
+            run("cd dir", 3, &[InputAction::TransposeWords]),
+            ("dir cd".into(), 6)
+        );
+        assert_eq!(
+            run("git push origin", 4, &[InputAction::KillWordForward]),
+            ("git  origin".into(), 4)
+        );
+        let (_, cursor) = run("foo-bar baz", 0, &[InputAction::MoveWordRight]);
+        assert_eq!(cursor, 3);
+    }
+
+    #[test]
+    fn vi_operators_with_motions_and_text_objects() {
+        let w = ViMotion::WordForward { big: false };
+        assert_eq!(
+            run("echo hello world", 5, &[operate(ViOperator::Delete, w)]),
+            ("echo world".into(), 5)
+        );
+        assert_eq!(
+            run("echo hello world", 5, &[operate(ViOperator::Change, w)]),
+            ("echo  world".into(), 5)
+        );
+        let aw = ViMotion::Word {
+            big: false,
+            around: true,
+        };
+        assert_eq!(
+            run("echo hello world", 7, &[operate(ViOperator::Delete, aw)]),
+            ("echo world".into(), 5)
+        );
+        let find = ViMotion::Find {
+            target: 'o',
+            forward: true,
+            till: true,
+        };
+        assert_eq!(
+            run("cat foo.txt", 0, &[operate(ViOperator::Delete, find)]),
+            ("oo.txt".into(), 0)
+        );
+        assert_eq!(
+            run(
+                "ls -la",
+                0,
+                &[
+                    operate(ViOperator::Yank, ViMotion::WordEnd { big: true }),
+                    InputAction::ViPut {
+                        before: false,
+                        count: 1
+                    },
+                ],
+            ),
+            ("llss -la".into(), 2)
+        );
+    }
+
+    #[test]
+    fn vi_moves_stay_on_the_line() {
+        let (_, cursor) = run(
+            "a b",
+            0,
+            &[InputAction::ViMove {
+                motion: ViMotion::LineEnd,
+                count: 1,
+            }],
+        );
+        assert_eq!(cursor, 2);
+        let (_, cursor) = run(
+            "abc def",
+            6,
+            &[InputAction::ViMove {
+                motion: ViMotion::WordBackward { big: false },
+                count: 1,
+            }],
+        );
+        assert_eq!(cursor, 4);
+        assert_eq!(
+            run(
+                "abc",
+                0,
+                &[InputAction::ViReplaceChar {
+                    replacement: 'x',
+                    count: 2
+                }]
+            ),
+            ("xxc".into(), 1)
+        );
+    }
+}
@@ -1,12 +1,21 @@
 //! Advanced history management for NexusShell CUI
 //! Provides persistent history with search, filtering, and deduplication
-
-use chrono::{DateTime, Utc};
+//!
+//! The history file (`~/.nxsh_history` by default) holds one JSON entry per
+//! line and is only ever appended to, so several shells can share it. Each
+//! session writes whole lines in a single append and, before adding its own
+//! commands, picks up the lines other sessions appended since it last looked.
+//! Appends are fsynced in batches; the file is rewritten only to trim it to
+//! the configured size and age, at startup and on exit.
+
+use crate::config::UiConfig;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
 
 /// A single history entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +49,18 @@ impl HistoryEntry {
         self.session_id = Some(session_id);
         self
     }
+
+    /// Parse a line of the history file; lines that are not JSON come from
+    /// plain-text history files and become bare commands
+    fn from_line(line: &str) -> Option<Self> {
+        if line.trim().is_empty() {
+            return None;
+        }
+        Some(serde_json::from_str(line).unwrap_or_else(|_| Self {
+            working_directory: None,
+            ..Self::new(line.to_string())
+        }))
+    }
 }
 
 /// History configuration
@@ -53,6 +74,12 @@ pub struct HistoryConfig {
     pub ignore_space_prefixed: bool,
     pub save_exit_codes: bool,
     pub save_working_directory: bool,
+    /// Entries older than this are dropped when the history is loaded or trimmed
+    pub max_age: Option<Duration>,
+    /// Appended entries are fsynced once this many are unsynced
+    pub sync_batch: usize,
+    /// Pick up commands that other running shells append to the same file
+    pub share_between_sessions: bool,
 }
 
 impl Default for HistoryConfig {
@@ -69,10 +96,59 @@ impl Default for HistoryConfig {
             ignore_space_prefixed: true,
             save_exit_codes: true,
             save_working_directory: true,
+            max_age: None,
+            sync_batch: 8,
+            share_between_sessions: true,
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// History settings taken from the UI configuration
+    pub fn from_ui_config(ui: &UiConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_entries: ui.history_max_entries,
+            file_path: ui.history_file.clone().or(defaults.file_path),
+            max_age: ui
+                .history_max_age_days
+                .map(|days| Duration::days(days.min(365_000) as i64)),
+            sync_batch: ui.history_sync_batch,
+            ..defaults
         }
     }
 }
 
+/// Prefix index over history commands for autosuggestions: each distinct
+/// command maps to when it was last run, and a prefix lookup is a range
+/// scan over the ordered keys, so it stays fast as history grows
+#[derive(Debug, Default)]
+struct PrefixIndex {
+    last_used: BTreeMap<String, u64>,
+    sequence: u64,
+}
+
+impl PrefixIndex {
+    fn insert(&mut self, command: &str) {
+        self.sequence += 1;
+        self.last_used.insert(command.to_string(), self.sequence);
+    }
+
+    /// The most recently run command that extends `prefix`
+    fn suggest(&self, prefix: &str) -> Option<&str> {
+        self.last_used
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(command, _)| command.starts_with(prefix))
+            .filter(|(command, _)| command.len() > prefix.len())
+            .max_by_key(|&(_, &sequence)| sequence)
+            .map(|(command, _)| command.as_str())
+    }
+
+    fn clear(&mut self) {
+        self.last_used.clear();
+    }
+}
+
 /// Advanced history manager
 pub struct History {
     entries: VecDeque<HistoryEntry>,
@@ -80,6 +156,14 @@ pub struct History {
     current_index: Option<usize>,
     search_results: Vec<usize>,
     session_id: String,
+    /// Entries not yet appended to the file; the newest waits for its exit code
+    pending: Vec<HistoryEntry>,
+    /// Entries appended since the file was last fsynced
+    unsynced: usize,
+    /// How much of the history file has been read into `entries`
+    file_len: u64,
+    /// Commands by prefix, for autosuggestions
+    index: PrefixIndex,
 }
 
 impl Default for History {
@@ -100,12 +184,19 @@ impl History {
             current_index: None,
             search_results: Vec::new(),
             session_id: uuid::Uuid::new_v4().to_string(),
+            pending: Vec::new(),
+            unsynced: 0,
+            file_len: 0,
+            index: PrefixIndex::default(),
         };
 
         if history.config.persist_to_file {
             if let Err(e) = history.load_from_file() {
                 eprintln!("Warning: Failed to load history: {}", e);
             }
+            if let Err(e) = history.trim_file() {
+                eprintln!("Warning: Failed to trim history: {}", e);
+            }
         }
 
         history
@@ -132,10 +223,22 @@ impl History {
             }
         }
 
-        let entry =
+        // Commands still waiting for an exit code are done by now
+        self.flush_pending();
+        if self.config.persist_to_file && self.config.share_between_sessions {
+            if let Err(e) = self.merge_from_file() {
+                eprintln!("Warning: Failed to merge history: {}", e);
+            }
+        }
+
+        let mut entry =
             HistoryEntry::new(command.trim().to_string()).with_session_id(self.session_id.clone());
+        if !self.config.save_working_directory {
+            entry.working_directory = None;
+        }
 
-        self.entries.push_back(entry);
+        self.push_entry(entry.clone());
+        self.pending.push(entry);
 
         // Maintain size limit
         while self.entries.len() > self.config.max_entries {
@@ -147,14 +250,47 @@ impl History {
             self.deduplicate();
         }
 
-        // Save to file if configured
-        if self.config.persist_to_file {
-            if let Err(e) = self.save_to_file() {
-                eprintln!("Warning: Failed to save history: {}", e);
+        self.current_index = None;
+    }
+
+    /// Record how the most recently added command exited and append it to
+    /// the history file
+    pub fn record_exit_code(&mut self, exit_code: i32) {
+        if self.config.save_exit_codes {
+            if let Some(pending) = self.pending.last_mut() {
+                pending.exit_code = Some(exit_code);
+                let timestamp = pending.timestamp;
+                if let Some(entry) =
+                    self.entries.iter_mut().rev().find(|entry| {
+                        entry.timestamp == timestamp && entry.command == pending.command
+                    })
+                {
+                    entry.exit_code = Some(exit_code);
+                }
+            }
+        }
+        self.flush_pending();
+    }
+
+    /// Append everything not yet written and fsync the history file
+    pub fn sync(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.append_pending()?;
+        if self.unsynced > 0 {
+            if let Some(file_path) = self.file_path() {
+                OpenOptions::new()
+                    .append(true)
+                    .open(file_path)?
+                    .sync_data()?;
             }
+            self.unsynced = 0;
         }
+        Ok(())
+    }
 
-        self.current_index = None;
+    /// Pick up entries that other sessions appended to the history file
+    /// since it was last read
+    pub fn merge_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.read_file(false)
     }
 
     /// Get the previous entry in history
@@ -207,6 +343,36 @@ impl History {
             .collect()
     }
 
+    /// The most recent command that starts with `prefix` and is longer than
+    /// it, for showing as an autosuggestion
+    pub fn suggest(&self, prefix: &str) -> Option<&str> {
+        if prefix.is_empty() {
+            return None;
+        }
+        self.index.suggest(prefix)
+    }
+
+    /// Entries run in `directory`, oldest first
+    pub fn search_in_directory(&self, directory: &Path) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .working_directory
+                    .as_deref()
+                    .is_some_and(|dir| Path::new(dir) == directory)
+            })
+            .collect()
+    }
+
+    /// Entries that exited unsuccessfully, oldest first
+    pub fn failed(&self) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.exit_code.is_some_and(|code| code != 0))
+            .collect()
+    }
+
     /// Reverse search (like Ctrl+R)
     pub fn reverse_search(&mut self, query: &str) -> Option<String> {
         let query_lower = query.to_lowercase();
@@ -239,12 +405,22 @@ impl History {
     /// Clear all history
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.index.clear();
+        self.pending.clear();
         self.current_index = None;
         self.search_results.clear();
 
-        if self.config.persist_to_file {
-            if let Err(e) = self.save_to_file() {
-                eprintln!("Warning: Failed to save cleared history: {}", e);
+        if let Some(file_path) = self.file_path() {
+            match Self::open_options()
+                .write(true)
+                .truncate(true)
+                .open(file_path)
+            {
+                Ok(_) => {
+                    self.file_len = 0;
+                    self.unsynced = 0;
+                }
+                Err(e) => eprintln!("Warning: Failed to save cleared history: {}", e),
             }
         }
     }
@@ -293,59 +469,172 @@ impl History {
         self.entries = unique_entries;
     }
 
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        self.index.insert(&entry.command);
+        self.entries.push_back(entry);
+    }
+
+    /// The history file, when history is persisted
+    fn file_path(&self) -> Option<&Path> {
+        self.config
+            .file_path
+            .as_deref()
+            .filter(|_| self.config.persist_to_file)
+    }
+
+    fn expired(&self, entry: &HistoryEntry) -> bool {
+        self.config
+            .max_age
+            .is_some_and(|max_age| Utc::now() - entry.timestamp > max_age)
+    }
+
     fn load_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(file_path) = &self.config.file_path {
-            if file_path.exists() {
-                let file = File::open(file_path)?;
-                let reader = BufReader::new(file);
-
-                for line in reader.lines() {
-                    let line = line?;
-                    if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
-                        self.entries.push_back(entry);
-                    } else {
-                        // Fallback for plain text format
-                        if !line.trim().is_empty() {
-                            self.entries.push_back(HistoryEntry::new(line));
-                        }
-                    }
-                }
+        self.read_file(true)
+    }
 
-                // Maintain size limit
-                while self.entries.len() > self.config.max_entries {
-                    self.entries.pop_front();
-                }
+    /// Read the history file from where the last read stopped. A full
+    /// reload, or a file that shrank because another shell trimmed it, starts
+    /// over from the beginning; otherwise this session's own lines are
+    /// already in memory and are skipped.
+    fn read_file(&mut self, reload: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(file_path) = self.file_path() else {
+            return Ok(());
+        };
+        let mut file = match File::open(file_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let reload = reload || file.metadata()?.len() < self.file_len;
+        if reload {
+            self.entries.clear();
+            self.index.clear();
+            self.file_len = 0;
+        }
+        file.seek(SeekFrom::Start(self.file_len))?;
+
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            // A line without its newline is still being written
+            if read == 0 || !line.ends_with('\n') {
+                break;
+            }
+            self.file_len += read as u64;
+            let Some(entry) = HistoryEntry::from_line(line.trim_end()) else {
+                continue;
+            };
+            let own = entry.session_id.as_deref() == Some(self.session_id.as_str());
+            if (reload || !own) && !self.expired(&entry) {
+                self.push_entry(entry);
             }
         }
+        if reload {
+            for entry in self.pending.clone() {
+                self.push_entry(entry);
+            }
+        }
+
+        // Maintain size limit
+        while self.entries.len() > self.config.max_entries {
+            self.entries.pop_front();
+        }
 
         Ok(())
     }
 
-    fn save_to_file(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(file_path) = &self.config.file_path {
-            // Create parent directories if they don't exist
-            if let Some(parent) = file_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+    /// Append entries that are not yet in the file, reporting failures
+    fn flush_pending(&mut self) {
+        if let Err(e) = self.append_pending() {
+            eprintln!("Warning: Failed to save history: {}", e);
+        }
+    }
 
-            let file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(file_path)?;
+    fn append_pending(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(file_path) = self.file_path() else {
+            self.pending.clear();
+            return Ok(());
+        };
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        // Create parent directories if they don't exist
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
 
-            let mut writer = BufWriter::new(file);
+        // One write for the whole batch, so lines from shells appending at
+        // the same time never interleave
+        let mut batch = String::new();
+        for entry in &self.pending {
+            batch.push_str(&serde_json::to_string(entry)?);
+            batch.push('\n');
+        }
+        let mut file = Self::open_options().append(true).open(file_path)?;
+        file.write_all(batch.as_bytes())?;
+
+        self.unsynced += self.pending.len();
+        self.pending.clear();
+        if self.unsynced >= self.config.sync_batch.max(1) {
+            file.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
 
-            for entry in &self.entries {
-                let json = serde_json::to_string(entry)?;
-                writeln!(writer, "{}", json)?;
-            }
+    /// Rewrite the history file without entries beyond `max_entries` or
+    /// older than `max_age`. The rewrite replaces the file by rename, so it
+    /// is kept rare: a small overshoot of `max_entries` is left alone.
+    fn trim_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(file_path) = self.file_path().map(Path::to_path_buf) else {
+            return Ok(());
+        };
+        let content = match std::fs::read_to_string(&file_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
 
-            writer.flush()?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+        let fresh: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| HistoryEntry::from_line(line).is_some_and(|e| !self.expired(&e)))
+            .collect();
+        let overflow = fresh.len().saturating_sub(self.config.max_entries);
+        let slack = self.config.max_entries / 10;
+        if fresh.len() == lines.len() && (overflow == 0 || overflow < slack) {
+            return Ok(());
         }
 
+        let temp_path = file_path.with_extension(format!("tmp.{}", std::process::id()));
+        {
+            let mut file = Self::open_options()
+                .write(true)
+                .truncate(true)
+                .open(&temp_path)?;
+            for line in &fresh[overflow..] {
+                writeln!(file, "{}", line)?;
+            }
+            file.sync_all()?;
+        }
+        std::fs::rename(&temp_path, &file_path)?;
+        self.file_len = std::fs::metadata(&file_path)?.len();
+        self.unsynced = 0;
         Ok(())
     }
+
+    /// Open options for the history file, which only its owner may read
+    fn open_options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+    }
 }
 
 /// History statistics
@@ -359,7 +648,7 @@ pub struct HistoryStats {
 impl Drop for History {
     fn drop(&mut self) {
         if self.config.persist_to_file {
-            if let Err(e) = self.save_to_file() {
+            if let Err(e) = self.sync().and_then(|_| self.trim_file()) {
                 eprintln!("Warning: Failed to save history on exit: {}", e);
             }
         }
@@ -370,6 +659,14 @@ impl Drop for History {
 mod tests {
     use super::*;
 
+    fn file_config(path: &Path) -> HistoryConfig {
+        HistoryConfig {
+            file_path: Some(path.to_path_buf()),
+            deduplicate: false,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_history_basic_operations() {
         let mut history = History::with_config(HistoryConfig {
@@ -402,4 +699,84 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].command, "ls -la");
     }
+
+    #[test]
+    fn test_history_suggests_most_recent_extension() {
+        let mut history = History::with_config(HistoryConfig {
+            persist_to_file: false,
+            ..Default::default()
+        });
+
+        history.add_entry("git status".to_string());
+        history.add_entry("git stash pop".to_string());
+        history.add_entry("ls".to_string());
+
+        assert_eq!(history.suggest("git st"), Some("git stash pop"));
+        assert_eq!(history.suggest("git statu"), Some("git status"));
+        assert_eq!(history.suggest("ls"), None);
+        assert_eq!(history.suggest(""), None);
+    }
+
+    #[test]
+    fn test_history_file_keeps_metadata_and_merges_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut first = History::with_config(file_config(&path));
+        let mut second = History::with_config(file_config(&path));
+        first.add_entry("make".to_string());
+        first.record_exit_code(2);
+        second.add_entry("ls".to_string());
+        second.record_exit_code(0);
+
+        // Each session sees the other's commands before adding its own
+        let commands: Vec<_> = second.entries().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, ["make", "ls"]);
+        first.add_entry("make test".to_string());
+        let commands: Vec<_> = first.entries().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, ["make", "ls", "make test"]);
+        drop(first);
+        drop(second);
+
+        let reloaded = History::with_config(file_config(&path));
+        let entries: Vec<_> = reloaded.entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].exit_code, Some(2));
+        assert!(entries[0].working_directory.is_some());
+        assert_eq!(reloaded.failed().len(), 1);
+    }
+
+    #[test]
+    fn test_history_file_is_trimmed_by_size_and_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let old = HistoryEntry {
+            timestamp: Utc::now() - Duration::days(30),
+            ..HistoryEntry::new("old".to_string())
+        };
+        std::fs::write(
+            &path,
+            format!("{}\nplain\n", serde_json::to_string(&old).unwrap()),
+        )
+        .unwrap();
+
+        let mut history = History::with_config(HistoryConfig {
+            max_entries: 2,
+            max_age: Some(Duration::days(7)),
+            ..file_config(&path)
+        });
+        assert_eq!(history.entries().count(), 1);
+        for command in ["a", "b", "c"] {
+            history.add_entry(command.to_string());
+        }
+        drop(history);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let commands: Vec<_> = content
+            .lines()
+            .filter_map(HistoryEntry::from_line)
+            .map(|e| e.command)
+            .collect();
+        assert_eq!(commands, ["b", "c"]);
+    }
 }
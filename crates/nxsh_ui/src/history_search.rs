@@ -0,0 +1,437 @@
+//! Interactive fuzzy history search
+//!
+//! Ctrl-R opens an overlay below the input line: typing narrows the history
+//! with fuzzy matching, Up/Down move the selection, Enter puts the selected
+//! command on the line and Esc leaves the line as it was. A preview under the
+//! list shows the selected command in full with when and where it ran.
+//! Drawing uses plain crossterm commands, like the completion panel.
+
+use crate::history::{History, HistoryEntry};
+use crate::input_handler::KeyEvent;
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyModifiers},
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal, QueueableCommand,
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Most matches kept for the overlay to scroll through
+const MAX_MATCHES: usize = 200;
+/// Most match rows shown at once
+const LIST_ROWS: usize = 8;
+/// Most rows the preview wraps the selected command over
+const PREVIEW_COMMAND_ROWS: usize = 3;
+const HEADER: &str = "history> ";
+
+/// A history entry matching a search
+#[derive(Debug, Clone)]
+pub struct HistoryMatch {
+    pub entry: HistoryEntry,
+    pub score: i64,
+    /// Char indices of the command that matched the query
+    pub positions: Vec<usize>,
+}
+
+/// Rank history against a fuzzy query, best first. Each command appears
+/// once, for its latest run; equal scores keep the more recent command
+/// first, and an empty query lists commands newest first.
+pub fn rank(history: &History, query: &str, limit: usize) -> Vec<HistoryMatch> {
+    let matcher = SkimMatcherV2::default().smart_case();
+    let mut seen = HashSet::new();
+    let mut matches = Vec::new();
+
+    for entry in history.recent(usize::MAX) {
+        if !seen.insert(entry.command.as_str()) {
+            continue;
+        }
+        let (score, positions) = if query.is_empty() {
+            (0, Vec::new())
+        } else {
+            match matcher.fuzzy_indices(&entry.command, query) {
+                Some(found) => found,
+                None => continue,
+            }
+        };
+        matches.push(HistoryMatch {
+            entry: entry.clone(),
+            score,
+            positions,
+        });
+    }
+
+    // Stable, so ties stay newest first
+    matches.sort_by(|a, b| b.score.cmp(&a.score));
+    matches.truncate(limit);
+    matches
+}
+
+/// What the overlay did with a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchStep {
+    /// Still searching
+    Continue,
+    /// Put this command on the input line
+    Accept(String),
+    /// Close the overlay, leaving the line alone
+    Cancel,
+}
+
+/// State of an open history search overlay
+#[derive(Debug, Clone)]
+pub struct HistorySearch {
+    query: String,
+    matches: Vec<HistoryMatch>,
+    total: usize,
+    selected: usize,
+    /// First match shown in the list
+    scroll: usize,
+}
+
+impl HistorySearch {
+    /// Open a search seeded with `query`, usually the line typed so far
+    pub fn new(query: &str, history: &History) -> Self {
+        let mut search = Self {
+            query: query.to_string(),
+            matches: Vec::new(),
+            total: 0,
+            selected: 0,
+            scroll: 0,
+        };
+        search.update(history);
+        search
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn matches(&self) -> &[HistoryMatch] {
+        &self.matches
+    }
+
+    pub fn selected(&self) -> Option<&HistoryMatch> {
+        self.matches.get(self.selected)
+    }
+
+    /// Re-run the search after the query or history changed
+    pub fn update(&mut self, history: &History) {
+        self.matches = rank(history, &self.query, MAX_MATCHES);
+        self.total = history.entries().count();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, history: &History) -> SearchStep {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => {
+                return match self.selected() {
+                    Some(found) => SearchStep::Accept(found.entry.command.clone()),
+                    None => SearchStep::Cancel,
+                };
+            }
+            KeyCode::Esc => return SearchStep::Cancel,
+            KeyCode::Char('c' | 'g') if ctrl => return SearchStep::Cancel,
+            KeyCode::Up | KeyCode::BackTab => self.select_previous(),
+            KeyCode::Char('p') if ctrl => self.select_previous(),
+            KeyCode::Down | KeyCode::Tab => self.select_next(),
+            KeyCode::Char('n' | 'r') if ctrl => self.select_next(),
+            KeyCode::Backspace => self.delete_char(history),
+            KeyCode::Char('h') if ctrl => self.delete_char(history),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.update(history);
+            }
+            KeyCode::Char('w') if ctrl => {
+                let kept = self.query.trim_end().rfind(' ').map_or(0, |i| i + 1);
+                self.query.truncate(kept);
+                self.update(history);
+            }
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.query.push(c);
+                self.update(history);
+            }
+            _ => {}
+        }
+        SearchStep::Continue
+    }
+
+    fn delete_char(&mut self, history: &History) {
+        if self.query.pop().is_some() {
+            self.update(history);
+        }
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Rows `draw` will use for a screen `width` columns wide
+    pub fn height(&self, width: usize) -> usize {
+        1 + self.matches.len().clamp(1, LIST_ROWS) + 1 + self.preview_lines(width).len()
+    }
+
+    /// Column of the caret, at the end of the query on the first row
+    pub fn caret_column(&self) -> usize {
+        HEADER.len() + UnicodeWidthStr::width(self.query.as_str())
+    }
+
+    /// Draw the overlay from row `top` down, at most `max_rows` rows.
+    /// Returns the number of rows drawn.
+    pub fn draw<W: Write>(
+        &mut self,
+        out: &mut W,
+        top: u16,
+        width: usize,
+        max_rows: usize,
+    ) -> io::Result<usize> {
+        let mut rows = Vec::new();
+
+        let count = format!("{}/{}", self.matches.len(), self.total);
+        let used = HEADER.len() + UnicodeWidthStr::width(self.query.as_str());
+        let gap = width.saturating_sub(used + count.len() + 1).max(1);
+        rows.push(vec![
+            (Some(Color::Cyan), HEADER.to_string()),
+            (None, self.query.clone()),
+            (None, " ".repeat(gap)),
+            (Some(Color::DarkGrey), count),
+        ]);
+
+        // Keep the selection inside the visible part of the list
+        let list_rows = LIST_ROWS.min(max_rows.saturating_sub(2)).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+        if self.matches.is_empty() {
+            rows.push(vec![(Some(Color::DarkGrey), "  no matches".to_string())]);
+        }
+        for (i, found) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_rows)
+        {
+            rows.push(match_row(found, i == self.selected, width));
+        }
+
+        if let Some(found) = self.selected() {
+            rows.push(vec![(Some(Color::DarkGrey), describe(&found.entry))]);
+        }
+        for line in self.preview_lines(width) {
+            rows.push(vec![(None, line)]);
+        }
+
+        rows.truncate(max_rows);
+        for (i, spans) in rows.iter().enumerate() {
+            out.queue(cursor::MoveTo(0, top + i as u16))?;
+            out.queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+            for (color, text) in spans {
+                match color {
+                    Some(color) => {
+                        out.queue(SetForegroundColor(*color))?;
+                        out.queue(Print(text))?;
+                        out.queue(ResetColor)?;
+                    }
+                    None => {
+                        out.queue(Print(text))?;
+                    }
+                }
+            }
+        }
+        Ok(rows.len())
+    }
+
+    /// The selected command wrapped to the screen width
+    fn preview_lines(&self, width: usize) -> Vec<String> {
+        let Some(found) = self.selected() else {
+            return Vec::new();
+        };
+        let width = width.max(8) - 1;
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut used = 0;
+        for c in printable(&found.entry.command).chars() {
+            let w = UnicodeWidthChar::width(c).unwrap_or(0);
+            if used + w > width {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+            line.push(c);
+            used += w;
+        }
+        lines.push(line);
+        if lines.len() > PREVIEW_COMMAND_ROWS {
+            lines.truncate(PREVIEW_COMMAND_ROWS);
+            if let Some(last) = lines.last_mut() {
+                last.pop();
+                last.push('…');
+            }
+        }
+        lines
+    }
+}
+
+/// A list row: the command cut to fit, with matched chars highlighted
+fn match_row(found: &HistoryMatch, selected: bool, width: usize) -> Vec<(Option<Color>, String)> {
+    let plain = if selected { Color::Cyan } else { Color::Reset };
+    let mut spans = vec![(Some(plain), if selected { "> " } else { "  " }.to_string())];
+    let mut used = 2;
+    for (i, c) in printable(&found.entry.command).chars().enumerate() {
+        let w = UnicodeWidthChar::width(c).unwrap_or(0);
+        if used + w + 1 > width {
+            spans.push((Some(Color::DarkGrey), "…".to_string()));
+            break;
+        }
+        used += w;
+        let color = if found.positions.contains(&i) {
+            Color::Yellow
+        } else {
+            plain
+        };
+        match spans.last_mut() {
+            Some((Some(last), text)) if *last == color => text.push(c),
+            _ => spans.push((Some(color), c.to_string())),
+        }
+    }
+    spans
+}
+
+/// When and where a command ran, for the preview
+fn describe(entry: &HistoryEntry) -> String {
+    let mut parts = vec![entry
+        .timestamp
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()];
+    if let Some(directory) = &entry.working_directory {
+        let home = dirs::home_dir().and_then(|home| home.to_str().map(str::to_string));
+        parts.push(match home {
+            Some(home) if directory.starts_with(&home) => {
+                format!("~{}", &directory[home.len()..])
+            }
+            _ => directory.clone(),
+        });
+    }
+    if let Some(code) = entry.exit_code {
+        parts.push(format!("exit {}", code));
+    }
+    parts.join("  ")
+}
+
+/// Show newlines and tabs in multi-line commands as single visible chars
+fn printable(command: &str) -> String {
+    command
+        .chars()
+        .map(|c| match c {
+            '\n' => '⏎',
+            '\t' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryConfig;
+
+    fn history(commands: &[&str]) -> History {
+        let mut history = History::with_config(HistoryConfig {
+            persist_to_file: false,
+            ..Default::default()
+        });
+        for command in commands {
+            history.add_entry(command.to_string());
+        }
+        history
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn rank_is_fuzzy_deduplicated_and_newest_first() {
+        let history = history(&["git status", "cargo test", "git stash", "git status"]);
+
+        let all: Vec<_> = rank(&history, "", 10)
+            .into_iter()
+            .map(|m| m.entry.command)
+            .collect();
+        assert_eq!(all, ["git status", "git stash", "cargo test"]);
+
+        let found = rank(&history, "ctst", 10);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].entry.command, "cargo test");
+        assert_eq!(found[0].positions.len(), 4);
+        assert!(rank(&history, "zzz", 10).is_empty());
+    }
+
+    #[test]
+    fn keys_filter_navigate_and_accept() {
+        let history = history(&["ls -la", "git stash", "git status"]);
+        let mut search = HistorySearch::new("", &history);
+        assert_eq!(search.matches().len(), 3);
+
+        for c in "st".chars() {
+            assert_eq!(
+                search.handle_key(key(KeyCode::Char(c)), &history),
+                SearchStep::Continue
+            );
+        }
+        assert_eq!(search.query(), "st");
+        assert_eq!(search.matches().len(), 2);
+
+        // The selection stops at the last match
+        search.handle_key(key(KeyCode::Down), &history);
+        search.handle_key(key(KeyCode::Down), &history);
+        let last = search.matches()[1].entry.command.clone();
+        assert_eq!(
+            search.handle_key(key(KeyCode::Enter), &history),
+            SearchStep::Accept(last)
+        );
+
+        for c in "at".chars() {
+            search.handle_key(key(KeyCode::Char(c)), &history);
+        }
+        assert_eq!(
+            search.handle_key(key(KeyCode::Enter), &history),
+            SearchStep::Accept("git status".to_string())
+        );
+
+        search.handle_key(key(KeyCode::Backspace), &history);
+        assert_eq!(search.query(), "sta");
+        assert_eq!(
+            search.handle_key(key(KeyCode::Esc), &history),
+            SearchStep::Cancel
+        );
+    }
+
+    #[test]
+    fn draw_fits_the_rows_it_is_given() {
+        let history = history(&["echo one", "echo two\necho three"]);
+        let mut search = HistorySearch::new("echo", &history);
+        let mut out = Vec::new();
+        let height = search.height(40);
+        assert_eq!(search.draw(&mut out, 0, 40, 20).unwrap(), height);
+        assert_eq!(search.draw(&mut out, 0, 40, 2).unwrap(), 2);
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.contains("echo two⏎echo three"));
+    }
+}
@@ -1,6 +1,14 @@
 //! Advanced input handling for NexusShell CUI
 //! Provides sophisticated key binding, input processing, and interactive features
+//!
+//! Two editing modes are supported. Emacs mode maps keys straight to
+//! actions. Vi mode is modal: insert mode works like a small Emacs keymap,
+//! and `Esc` switches to normal mode, where keys build commands such as
+//! `3w`, `dw`, `ciw` or `f.` that become a single [`InputAction`]. Bindings
+//! can be remapped from [`UiConfig`] using readline's command names, e.g.
+//! `"Ctrl+T" = "transpose-chars"`.
 
+use crate::config::UiConfig;
 use crossterm::event::{KeyCode, KeyEvent as CrosstermKeyEvent, KeyModifiers};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
@@ -49,20 +57,55 @@ pub enum InputAction {
 
     // Line editing
     DeleteWord,
+    KillWordForward,
     DeleteToEnd,
     DeleteToStart,
     DeleteLine,
+    TransposeChars,
+    TransposeWords,
+    Undo,
 
     // Clipboard
     Copy,
     Cut,
     Paste,
+    Yank,
+    YankPop,
 
     // Control
     Submit,
     Cancel,
     Interrupt,
     Suspend,
+    /// Ctrl-D: end of input on an empty line, otherwise delete a char
+    EndOfInput,
+    /// Accept the autosuggestion up to the end of its next word
+    AcceptSuggestionWord,
+
+    // Vi mode
+    /// Leave insert mode for normal mode
+    ViCommandMode,
+    /// Enter insert mode, first moving the cursor
+    ViInsert(ViInsertPosition),
+    ViMove {
+        motion: ViMotion,
+        count: usize,
+    },
+    ViOperate {
+        operator: ViOperator,
+        motion: ViMotion,
+        count: usize,
+    },
+    /// `p` / `P`: put the last deleted or yanked text after or before the cursor
+    ViPut {
+        before: bool,
+        count: usize,
+    },
+    /// `r`: replace chars under the cursor
+    ViReplaceChar {
+        replacement: char,
+        count: usize,
+    },
 
     // Special
     ClearScreen,
@@ -73,6 +116,126 @@ pub enum InputAction {
     Custom(String),
 }
 
+impl InputAction {
+    /// Action for a readline command name, as used in keybinding tables
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "self-insert" => return None,
+            "backward-delete-char" => InputAction::Backspace,
+            "delete-char" => InputAction::Delete,
+            "backward-char" => InputAction::MoveLeft,
+            "forward-char" => InputAction::MoveRight,
+            "backward-word" => InputAction::MoveWordLeft,
+            "forward-word" => InputAction::MoveWordRight,
+            "beginning-of-line" => InputAction::MoveToStart,
+            "end-of-line" => InputAction::MoveToEnd,
+            "previous-history" => InputAction::HistoryPrevious,
+            "next-history" => InputAction::HistoryNext,
+            "reverse-search-history" => InputAction::HistorySearch,
+            "complete" => InputAction::Complete,
+            "menu-complete" => InputAction::CompleteNext,
+            "menu-complete-backward" => InputAction::CompletePrevious,
+            "unix-word-rubout" | "backward-kill-word" => InputAction::DeleteWord,
+            "kill-word" => InputAction::KillWordForward,
+            "kill-line" => InputAction::DeleteToEnd,
+            "unix-line-discard" | "backward-kill-line" => InputAction::DeleteToStart,
+            "kill-whole-line" => InputAction::DeleteLine,
+            "transpose-chars" => InputAction::TransposeChars,
+            "transpose-words" => InputAction::TransposeWords,
+            "undo" => InputAction::Undo,
+            "yank" => InputAction::Yank,
+            "yank-pop" => InputAction::YankPop,
+            "accept-line" => InputAction::Submit,
+            "abort" => InputAction::Cancel,
+            "interrupt" => InputAction::Interrupt,
+            "suspend" => InputAction::Suspend,
+            "delete-char-or-eof" | "end-of-file" => InputAction::EndOfInput,
+            "forward-suggestion-word" => InputAction::AcceptSuggestionWord,
+            "clear-screen" => InputAction::ClearScreen,
+            "redraw-current-line" => InputAction::Refresh,
+            "help" => InputAction::ShowHelp,
+            "vi-movement-mode" => InputAction::ViCommandMode,
+            "vi-insertion-mode" => InputAction::ViInsert(ViInsertPosition::Cursor),
+            "vi-append-mode" => InputAction::ViInsert(ViInsertPosition::After),
+            "vi-append-eol" => InputAction::ViInsert(ViInsertPosition::LineEnd),
+            "vi-insert-beg" => InputAction::ViInsert(ViInsertPosition::LineStart),
+            "vi-put" => InputAction::ViPut {
+                before: false,
+                count: 1,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Where Vi's insert commands (`i`, `a`, `I`, `A`) start inserting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViInsertPosition {
+    Cursor,
+    After,
+    LineStart,
+    LineEnd,
+}
+
+/// Vi operators that act on the text a motion covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViOperator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// Vi motions, and the word text objects used after an operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViMotion {
+    /// `h`
+    Left,
+    /// `l`
+    Right,
+    /// `w` / `W`
+    WordForward { big: bool },
+    /// `b` / `B`
+    WordBackward { big: bool },
+    /// `e` / `E`
+    WordEnd { big: bool },
+    /// `0`
+    LineStart,
+    /// `^`
+    FirstNonBlank,
+    /// `$`
+    LineEnd,
+    /// `f`, `F`, `t` and `T`
+    Find {
+        target: char,
+        forward: bool,
+        till: bool,
+    },
+    /// `iw`, `aw`, `iW` and `aW`
+    Word { big: bool, around: bool },
+    /// `dd`, `cc`, `yy`
+    WholeLine,
+}
+
+/// Line editing keymap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+/// Keys typed so far of an unfinished Vi normal-mode command
+#[derive(Debug, Clone, Copy, Default)]
+struct ViPending {
+    count: Option<usize>,
+    operator: Option<(ViOperator, Option<usize>)>,
+    /// `i` or `a` typed after an operator; `true` for `a`
+    object: Option<bool>,
+    /// `f`, `F`, `t` or `T` waiting for its char, as (forward, till)
+    find: Option<(bool, bool)>,
+    replace: bool,
+}
+
 /// Input mode for different editing behaviors
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputMode {
@@ -89,6 +252,8 @@ pub struct InputConfig {
     pub vi_mode: bool,
     pub emacs_bindings: bool,
     pub custom_bindings: HashMap<KeyEvent, InputAction>,
+    /// Bindings checked before the built-in Vi normal-mode commands
+    pub vi_command_bindings: HashMap<KeyEvent, InputAction>,
     pub timeout_ms: u64,
     pub enable_mouse: bool,
     pub enable_bracketed_paste: bool,
@@ -100,6 +265,7 @@ impl Default for InputConfig {
             vi_mode: false,
             emacs_bindings: true,
             custom_bindings: HashMap::new(),
+            vi_command_bindings: HashMap::new(),
             timeout_ms: 500,
             enable_mouse: false,
             enable_bracketed_paste: true,
@@ -107,6 +273,35 @@ impl Default for InputConfig {
     }
 }
 
+impl InputConfig {
+    /// Editing mode and key remappings from the UI configuration. Entries
+    /// with an unknown key or command name are skipped with a warning.
+    pub fn from_ui_config(ui: &UiConfig) -> Self {
+        let vi_mode = ui.edit_mode.eq_ignore_ascii_case("vi");
+        Self {
+            vi_mode,
+            emacs_bindings: !vi_mode,
+            custom_bindings: parse_bindings(&ui.keybindings),
+            vi_command_bindings: parse_bindings(&ui.vi_command_keybindings),
+            ..Self::default()
+        }
+    }
+}
+
+fn parse_bindings(table: &HashMap<String, String>) -> HashMap<KeyEvent, InputAction> {
+    let mut bindings = HashMap::new();
+    for (key, command) in table {
+        match (keys::parse(key), InputAction::from_name(command)) {
+            (Some(key), Some(action)) => {
+                bindings.insert(key, action);
+            }
+            (None, _) => log::warn!("keybinding: unknown key `{}`", key),
+            (_, None) => log::warn!("keybinding: unknown command `{}`", command),
+        }
+    }
+    bindings
+}
+
 /// Advanced input handler with key binding support
 pub struct InputHandler {
     config: InputConfig,
@@ -114,6 +309,7 @@ pub struct InputHandler {
     mode: InputMode,
     last_key_time: Option<Instant>,
     key_sequence: Vec<KeyEvent>,
+    vi: ViPending,
 }
 
 impl InputHandler {
@@ -127,6 +323,7 @@ impl InputHandler {
             mode: InputMode::Insert,
             last_key_time: None,
             key_sequence: Vec::new(),
+            vi: ViPending::default(),
             config,
         };
 
@@ -136,6 +333,57 @@ impl InputHandler {
 
     /// Process a key event and return the corresponding action
     pub fn handle_key(&mut self, key: KeyEvent) -> Option<InputAction> {
+        if self.config.vi_mode && self.mode == InputMode::Normal {
+            let action = self.vi_command_key(key);
+            if matches!(
+                action,
+                Some(InputAction::ViInsert(_))
+                    | Some(InputAction::ViOperate {
+                        operator: ViOperator::Change,
+                        ..
+                    })
+            ) {
+                self.mode = InputMode::Insert;
+            }
+            return action;
+        }
+        let action = self.insert_key(key);
+        if self.config.vi_mode && action == Some(InputAction::ViCommandMode) {
+            self.mode = InputMode::Normal;
+        }
+        action
+    }
+
+    /// Switch between the Emacs and Vi keymaps, keeping custom bindings
+    pub fn set_edit_mode(&mut self, mode: EditMode) {
+        if mode == self.edit_mode() {
+            return;
+        }
+        self.config.vi_mode = mode == EditMode::Vi;
+        self.config.emacs_bindings = mode == EditMode::Emacs;
+        self.key_bindings.clear();
+        self.setup_default_bindings();
+        self.start_line();
+    }
+
+    /// Current editing keymap
+    pub fn edit_mode(&self) -> EditMode {
+        if self.config.vi_mode {
+            EditMode::Vi
+        } else {
+            EditMode::Emacs
+        }
+    }
+
+    /// Reset per-line state before reading a new line: Vi starts each line
+    /// in insert mode
+    pub fn start_line(&mut self) {
+        self.mode = InputMode::Insert;
+        self.vi = ViPending::default();
+        self.key_sequence.clear();
+    }
+
+    fn insert_key(&mut self, key: KeyEvent) -> Option<InputAction> {
         let now = Instant::now();
 
         // Check for timeout in key sequences
@@ -165,7 +413,9 @@ impl InputHandler {
 
         // Default handling
         match key.code {
-            KeyCode::Char(c) if key.modifiers.is_empty() => Some(InputAction::InsertChar(c)),
+            KeyCode::Char(c) if (key.modifiers - KeyModifiers::SHIFT).is_empty() => {
+                Some(InputAction::InsertChar(c))
+            }
             _ => None,
         }
     }
@@ -359,7 +609,7 @@ impl InputHandler {
                 code: Char('d'),
                 modifiers: KeyModifiers::CONTROL,
             },
-            InputAction::Delete,
+            InputAction::EndOfInput,
         );
         self.bind_key(
             KeyEvent {
@@ -438,7 +688,7 @@ impl InputHandler {
                 code: Char('d'),
                 modifiers: KeyModifiers::ALT,
             },
-            InputAction::DeleteWord,
+            InputAction::KillWordForward,
         );
         self.bind_key(
             KeyEvent {
@@ -447,44 +697,257 @@ impl InputHandler {
             },
             InputAction::DeleteWord,
         );
+
+        // Kill ring, transposition and undo
+        let bindings = [
+            (keys::ctrl('y'), InputAction::Yank),
+            (keys::alt('y'), InputAction::YankPop),
+            (keys::ctrl('t'), InputAction::TransposeChars),
+            (keys::alt('t'), InputAction::TransposeWords),
+            (keys::ctrl('_'), InputAction::Undo),
+            (keys::ctrl('g'), InputAction::Cancel),
+            (keys::key(Esc), InputAction::Cancel),
+            (
+                KeyEvent {
+                    code: Right,
+                    modifiers: KeyModifiers::ALT,
+                },
+                InputAction::AcceptSuggestionWord,
+            ),
+        ];
+        for (key, action) in bindings {
+            self.bind_key(key, action);
+        }
     }
 
+    /// Vi insert-mode keys; normal mode is handled by `vi_command_key`
     fn setup_vi_bindings(&mut self) {
-        // Vi bindings would be mode-dependent
-        // This is a simplified version
+        use keys::{ctrl, key};
         use KeyCode::*;
 
-        // Insert mode bindings (similar to emacs for basic editing)
-        self.bind_key(
-            KeyEvent {
-                code: Backspace,
-                modifiers: KeyModifiers::NONE,
-            },
-            InputAction::Backspace,
-        );
-        self.bind_key(
-            KeyEvent {
-                code: Enter,
-                modifiers: KeyModifiers::NONE,
-            },
-            InputAction::Submit,
-        );
-        self.bind_key(
-            KeyEvent {
-                code: Tab,
-                modifiers: KeyModifiers::NONE,
-            },
-            InputAction::Complete,
-        );
+        let bindings = [
+            (key(Backspace), InputAction::Backspace),
+            (key(Delete), InputAction::Delete),
+            (key(Enter), InputAction::Submit),
+            (key(Tab), InputAction::Complete),
+            (keys::shift(BackTab), InputAction::CompletePrevious),
+            (key(Left), InputAction::MoveLeft),
+            (key(Right), InputAction::MoveRight),
+            (key(Home), InputAction::MoveToStart),
+            (key(End), InputAction::MoveToEnd),
+            (key(Up), InputAction::HistoryPrevious),
+            (key(Down), InputAction::HistoryNext),
+            (key(Esc), InputAction::ViCommandMode),
+            (ctrl('['), InputAction::ViCommandMode),
+            (ctrl('h'), InputAction::Backspace),
+            (ctrl('w'), InputAction::DeleteWord),
+            (ctrl('u'), InputAction::DeleteToStart),
+            (ctrl('c'), InputAction::Interrupt),
+            (ctrl('d'), InputAction::EndOfInput),
+            (ctrl('l'), InputAction::ClearScreen),
+            (ctrl('r'), InputAction::HistorySearch),
+            (ctrl('z'), InputAction::Suspend),
+        ];
+        for (key, action) in bindings {
+            self.bind_key(key, action);
+        }
+    }
 
-        // Escape to normal mode would be handled specially
-        self.bind_key(
-            KeyEvent {
-                code: Esc,
-                modifiers: KeyModifiers::NONE,
+    /// Handle a key in Vi normal mode, where keys accumulate into a
+    /// command: an optional count, an optional operator (`d`, `c`, `y`) and
+    /// a motion or text object. Returns an action once the command is complete.
+    fn vi_command_key(&mut self, key: KeyEvent) -> Option<InputAction> {
+        let pending = self.vi;
+        let idle = pending.count.is_none()
+            && pending.operator.is_none()
+            && pending.find.is_none()
+            && !pending.replace;
+        if idle {
+            if let Some(action) = self.config.vi_command_bindings.get(&key) {
+                return Some(action.clone());
+            }
+        }
+
+        let c = match key.code {
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.vi = ViPending::default();
+                return match c {
+                    'c' => Some(InputAction::Interrupt),
+                    'd' => Some(InputAction::EndOfInput),
+                    'l' => Some(InputAction::ClearScreen),
+                    'r' => Some(InputAction::HistorySearch),
+                    _ => None,
+                };
+            }
+            KeyCode::Char(c) => c,
+            code => {
+                self.vi = ViPending::default();
+                return match code {
+                    KeyCode::Enter => Some(InputAction::Submit),
+                    KeyCode::Left | KeyCode::Backspace => Some(InputAction::ViMove {
+                        motion: ViMotion::Left,
+                        count: 1,
+                    }),
+                    KeyCode::Right => Some(InputAction::ViMove {
+                        motion: ViMotion::Right,
+                        count: 1,
+                    }),
+                    KeyCode::Up => Some(InputAction::HistoryPrevious),
+                    KeyCode::Down => Some(InputAction::HistoryNext),
+                    KeyCode::Home => Some(InputAction::ViMove {
+                        motion: ViMotion::LineStart,
+                        count: 1,
+                    }),
+                    KeyCode::End => Some(InputAction::ViMove {
+                        motion: ViMotion::LineEnd,
+                        count: 1,
+                    }),
+                    _ => None,
+                };
+            }
+        };
+
+        let count = pending.count.unwrap_or(1);
+        if let Some((forward, till)) = pending.find {
+            return self.vi_motion(ViMotion::Find {
+                target: c,
+                forward,
+                till,
+            });
+        }
+        if pending.replace {
+            self.vi = ViPending::default();
+            return Some(InputAction::ViReplaceChar {
+                replacement: c,
+                count,
+            });
+        }
+        if let Some(around) = pending.object {
+            return match c {
+                'w' | 'W' => self.vi_motion(ViMotion::Word {
+                    big: c == 'W',
+                    around,
+                }),
+                _ => {
+                    self.vi = ViPending::default();
+                    None
+                }
+            };
+        }
+
+        let operator = pending.operator.map(|(operator, _)| operator);
+        let motion = match c {
+            '1'..='9' | '0' if c != '0' || pending.count.is_some() => {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                self.vi.count = Some(
+                    pending
+                        .count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                return None;
+            }
+            'd' | 'c' | 'y' => {
+                let typed = match c {
+                    'd' => ViOperator::Delete,
+                    'c' => ViOperator::Change,
+                    _ => ViOperator::Yank,
+                };
+                match operator {
+                    // `dd`, `cc`, `yy`
+                    Some(operator) if operator == typed => ViMotion::WholeLine,
+                    Some(_) => {
+                        self.vi = ViPending::default();
+                        return None;
+                    }
+                    None => {
+                        self.vi = ViPending {
+                            operator: Some((typed, pending.count)),
+                            ..ViPending::default()
+                        };
+                        return None;
+                    }
+                }
+            }
+            'i' | 'a' if operator.is_some() => {
+                self.vi.object = Some(c == 'a');
+                return None;
+            }
+            'f' | 'F' | 't' | 'T' => {
+                self.vi.find = Some((c.is_lowercase(), c == 't' || c == 'T'));
+                return None;
+            }
+            'h' => ViMotion::Left,
+            'l' | ' ' => ViMotion::Right,
+            'w' | 'W' => ViMotion::WordForward { big: c == 'W' },
+            'b' | 'B' => ViMotion::WordBackward { big: c == 'B' },
+            'e' | 'E' => ViMotion::WordEnd { big: c == 'E' },
+            '0' => ViMotion::LineStart,
+            '^' => ViMotion::FirstNonBlank,
+            '$' => ViMotion::LineEnd,
+            _ if operator.is_some() => {
+                self.vi = ViPending::default();
+                return None;
+            }
+            _ => {
+                self.vi = ViPending::default();
+                let (operator, motion) = match c {
+                    'x' => (ViOperator::Delete, ViMotion::Right),
+                    'X' => (ViOperator::Delete, ViMotion::Left),
+                    's' => (ViOperator::Change, ViMotion::Right),
+                    'D' => (ViOperator::Delete, ViMotion::LineEnd),
+                    'C' => (ViOperator::Change, ViMotion::LineEnd),
+                    'S' => (ViOperator::Change, ViMotion::WholeLine),
+                    'r' => {
+                        self.vi.count = pending.count;
+                        self.vi.replace = true;
+                        return None;
+                    }
+                    _ => {
+                        return match c {
+                            'i' => Some(InputAction::ViInsert(ViInsertPosition::Cursor)),
+                            'a' => Some(InputAction::ViInsert(ViInsertPosition::After)),
+                            'I' => Some(InputAction::ViInsert(ViInsertPosition::LineStart)),
+                            'A' => Some(InputAction::ViInsert(ViInsertPosition::LineEnd)),
+                            'p' | 'P' => Some(InputAction::ViPut {
+                                before: c == 'P',
+                                count,
+                            }),
+                            'u' => Some(InputAction::Undo),
+                            'k' | '-' => Some(InputAction::HistoryPrevious),
+                            'j' | '+' => Some(InputAction::HistoryNext),
+                            '/' | '?' => Some(InputAction::HistorySearch),
+                            _ => None,
+                        };
+                    }
+                };
+                return Some(InputAction::ViOperate {
+                    operator,
+                    motion,
+                    count,
+                });
+            }
+        };
+        self.vi_motion(motion)
+    }
+
+    /// Finish a Vi command with its motion, applying any pending operator
+    /// and multiplying the counts typed before and after it, as in `2d3w`
+    fn vi_motion(&mut self, motion: ViMotion) -> Option<InputAction> {
+        let pending = std::mem::take(&mut self.vi);
+        let motion_count = pending.count.unwrap_or(1);
+        Some(match pending.operator {
+            Some((operator, operator_count)) => InputAction::ViOperate {
+                operator,
+                motion,
+                count: operator_count.unwrap_or(1) * motion_count,
             },
-            InputAction::Custom("vi_normal_mode".to_string()),
-        );
+            None => InputAction::ViMove {
+                motion,
+                count: motion_count,
+            },
+        })
     }
 
     fn match_key_sequence(&self) -> Option<InputAction> {
@@ -543,6 +1006,62 @@ pub mod keys {
             modifiers: KeyModifiers::NONE,
         }
     }
+
+    /// Parse a key description such as `Ctrl+A`, `Alt+Backspace`, `Shift+Tab`,
+    /// `Esc` or `x`
+    pub fn parse(spec: &str) -> Option<KeyEvent> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        // `Ctrl++` binds the plus key
+        if spec.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let (name, prefixes) = parts.split_last()?;
+        for prefix in prefixes {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match name.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            lower => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                _ => {
+                    let mut chars = name.chars();
+                    let c = chars.next()?;
+                    if chars.next().is_some() {
+                        return None;
+                    }
+                    // Terminals report Ctrl and Alt letters in lower case
+                    if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                        KeyCode::Char(c.to_ascii_lowercase())
+                    } else {
+                        KeyCode::Char(c)
+                    }
+                }
+            },
+        };
+        Some(KeyEvent { code, modifiers })
+    }
 }
 
 #[cfg(test)]
@@ -562,6 +1081,110 @@ mod tests {
         assert_eq!(action, Some(InputAction::MoveToStart));
     }
 
+    #[test]
+    fn test_vi_normal_mode_builds_commands() {
+        let mut handler = InputHandler::new();
+        handler.set_edit_mode(EditMode::Vi);
+        assert_eq!(
+            handler.handle_key(keys::char('i')),
+            Some(InputAction::InsertChar('i'))
+        );
+        assert_eq!(
+            handler.handle_key(keys::key(KeyCode::Esc)),
+            Some(InputAction::ViCommandMode)
+        );
+        assert_eq!(handler.mode(), InputMode::Normal);
+
+        let mut typed = |keys: &str| {
+            keys.chars()
+                .filter_map(|c| handler.handle_key(keys::char(c)))
+                .last()
+        };
+        assert_eq!(
+            typed("2d3w"),
+            Some(InputAction::ViOperate {
+                operator: ViOperator::Delete,
+                motion: ViMotion::WordForward { big: false },
+                count: 6,
+            })
+        );
+        assert_eq!(
+            typed("dd"),
+            Some(InputAction::ViOperate {
+                operator: ViOperator::Delete,
+                motion: ViMotion::WholeLine,
+                count: 1,
+            })
+        );
+        assert_eq!(
+            typed("F/"),
+            Some(InputAction::ViMove {
+                motion: ViMotion::Find {
+                    target: '/',
+                    forward: false,
+                    till: false,
+                },
+                count: 1,
+            })
+        );
+        assert_eq!(
+            typed("ciw"),
+            Some(InputAction::ViOperate {
+                operator: ViOperator::Change,
+                motion: ViMotion::Word {
+                    big: false,
+                    around: false,
+                },
+                count: 1,
+            })
+        );
+        // `c` leaves normal mode, so keys insert again
+        assert_eq!(handler.mode(), InputMode::Insert);
+        assert_eq!(
+            handler.handle_key(keys::char('x')),
+            Some(InputAction::InsertChar('x'))
+        );
+    }
+
+    #[test]
+    fn test_bindings_from_ui_config() {
+        let mut ui = UiConfig {
+            edit_mode: "vi".to_string(),
+            ..UiConfig::default()
+        };
+        ui.keybindings
+            .insert("Ctrl+K".to_string(), "kill-whole-line".to_string());
+        ui.vi_command_keybindings
+            .insert("Q".to_string(), "accept-line".to_string());
+        ui.keybindings
+            .insert("Hyper+K".to_string(), "kill-line".to_string());
+
+        let mut handler = InputHandler::with_config(InputConfig::from_ui_config(&ui));
+        assert_eq!(handler.edit_mode(), EditMode::Vi);
+        assert_eq!(
+            handler.handle_key(keys::ctrl('k')),
+            Some(InputAction::DeleteLine)
+        );
+        handler.handle_key(keys::key(KeyCode::Esc));
+        assert_eq!(
+            handler.handle_key(keys::char('Q')),
+            Some(InputAction::Submit)
+        );
+
+        assert_eq!(
+            keys::parse("Alt+Backspace"),
+            Some(KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::ALT,
+            })
+        );
+        assert_eq!(
+            keys::parse("Shift+Tab"),
+            Some(keys::shift(KeyCode::BackTab))
+        );
+        assert_eq!(keys::parse("Ctrl+Shift"), None);
+    }
+
     #[test]
     fn test_custom_bindings() {
         let mut handler = InputHandler::new();
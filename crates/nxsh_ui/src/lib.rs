@@ -12,8 +12,9 @@
 // Re-export commonly used types and functions
 pub use completion::{CompletionResult, CompletionType, NexusCompleter};
 pub use config::UiConfig;
-pub use input_handler::{InputAction, InputHandler, InputMode, KeyEvent};
-pub use prompt::{PromptConfig, PromptRenderer, PromptStyle};
+pub use history::{History, HistoryConfig, HistoryEntry};
+pub use input_handler::{EditMode, InputAction, InputHandler, InputMode, KeyEvent};
+pub use prompt::{PromptConfig, PromptContext, PromptEngine, PromptRenderer, PromptStyle};
 pub use themes::{get_theme_by_name as get_theme, NexusTheme as Theme};
 
 use crossterm::{
@@ -26,14 +27,20 @@ use crossterm::{
 use std::io::{self, Write};
 
 // Core modules for binary dependencies
+pub mod abbreviations;
 pub mod ansi_render;
 pub mod completion;
 pub mod completion_engine;
 pub mod completion_panel;
 pub mod config;
+pub mod consent;
+pub mod editing;
 pub mod enhanced_line_editor;
 pub mod history;
+pub mod history_search;
 pub mod input_handler;
+pub mod menu;
+pub mod pager;
 pub mod prompt;
 pub mod readline;
 pub mod tab_completion;
@@ -239,12 +246,17 @@ pub struct AdvancedCuiController {
     theme: Theme,
     prompt: PromptRenderer,
     is_running: bool,
-    history: Vec<String>,
+    history: History,
 }
 
 impl AdvancedCuiController {
     /// Create a new advanced CUI controller
     pub fn new() -> anyhow::Result<Self> {
+        Self::with_config(&UiConfig::default())
+    }
+
+    /// Create a controller whose history file follows `config`
+    pub fn with_config(config: &UiConfig) -> anyhow::Result<Self> {
         let theme = get_theme("nxsh-dark-default")?;
         let prompt = PromptRenderer::new(PromptConfig::default());
 
@@ -252,7 +264,7 @@ impl AdvancedCuiController {
             theme,
             prompt,
             is_running: false,
-            history: Vec::new(),
+            history: History::with_config(HistoryConfig::from_ui_config(config)),
         })
     }
 
@@ -382,84 +394,24 @@ impl AdvancedCuiController {
             .collect()
     }
 
-    /// Add command to history with deduplication and size management
+    /// Add command to history; it is appended to the history file once
+    /// its exit code is recorded or the next command is added
     pub fn add_to_history(&mut self, command: String) {
-        // Skip empty commands and duplicates of the last command
-        if command.trim().is_empty() {
-            return;
-        }
-
-        if let Some(last) = self.history.last() {
-            if last == &command {
-                return; // Don't add duplicate consecutive commands
-            }
-        }
-
-        // Add the command
-        self.history.push(command);
-
-        // Maintain history size limit (default 10000)
-        const MAX_HISTORY_SIZE: usize = 10000;
-        if self.history.len() > MAX_HISTORY_SIZE {
-            self.history.drain(0..self.history.len() - MAX_HISTORY_SIZE);
-        }
+        self.history.add_entry(command);
     }
 
-    /// Search history with fuzzy matching and relevance scoring
-    pub fn search_history(&self, query: &str) -> Vec<&str> {
-        if query.is_empty() {
-            return self
-                .history
-                .iter()
-                .rev()
-                .take(20)
-                .map(|s| s.as_str())
-                .collect();
-        }
-
-        let query_lower = query.to_lowercase();
-        let mut matches: Vec<(usize, &str)> = Vec::new();
-
-        for (index, cmd) in self.history.iter().enumerate().rev() {
-            let cmd_lower = cmd.to_lowercase();
-
-            // Calculate relevance score
-            let score = if cmd_lower.starts_with(&query_lower) {
-                1000 + index // Exact prefix match gets highest score
-            } else if cmd_lower.contains(&query_lower) {
-                500 + index // Substring match gets medium score
-            } else if self.fuzzy_match(&cmd_lower, &query_lower) {
-                100 + index // Fuzzy match gets low score
-            } else {
-                continue;
-            };
-
-            matches.push((score, cmd.as_str()));
-        }
-
-        // Sort by score descending and take top 20 results
-        matches.sort_by(|a, b| b.0.cmp(&a.0));
-        matches.into_iter().take(20).map(|(_, cmd)| cmd).collect()
+    /// Record the exit code of the command last added to history
+    pub fn record_exit_code(&mut self, exit_code: i32) {
+        self.history.record_exit_code(exit_code);
     }
 
-    /// Simple fuzzy matching algorithm
-    fn fuzzy_match(&self, text: &str, pattern: &str) -> bool {
-        let mut text_chars = text.chars();
-        let pattern_chars = pattern.chars();
-
-        for pattern_char in pattern_chars {
-            let mut found = false;
-            for text_char in text_chars.by_ref() {
-                if text_char == pattern_char {
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                return false;
-            }
-        }
-        true
+    /// Search history with fuzzy matching, best matches first and each
+    /// command once
+    pub fn search_history(&self, query: &str) -> Vec<String> {
+        history_search::rank(&self.history, query, 20)
+            .into_iter()
+            .map(|found| found.entry.command)
+            .collect()
     }
 }
 
@@ -0,0 +1,75 @@
+//! The numbered menus of `select`
+//!
+//! [`render`] lays the choices of a `select` loop out as bash does: numbered
+//! from 1, running down the columns and then across, as many columns as fit
+//! the terminal width, followed by the `PS3` prompt. On a terminal, unless
+//! `NO_COLOR` is set, the numbers are drawn bold so the choices stand out.
+
+use std::io::{self, IsTerminal};
+
+/// The menu of `items` within `columns` followed by `prompt`; no items for
+/// the prompt alone
+pub fn render(items: &[String], prompt: &str, columns: usize) -> String {
+    let color =
+        io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    layout(items, prompt, columns, color)
+}
+
+fn layout(items: &[String], prompt: &str, columns: usize, color: bool) -> String {
+    let number_width = items.len().to_string().len();
+    let item_width = items.iter().map(|i| i.chars().count()).max().unwrap_or(0);
+    // "NN) item" plus two spaces of padding between columns
+    let cell_width = number_width + 2 + item_width + 2;
+    let per_row = (columns / cell_width.max(1)).max(1);
+    let rows = items.len().div_ceil(per_row);
+
+    let mut out = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        let mut idx = row;
+        while idx < items.len() {
+            let number = format!("{:>number_width$})", idx + 1);
+            let item = &items[idx];
+            idx += rows;
+            if color {
+                line.push_str(&format!("\x1b[1m{number}\x1b[0m {item}"));
+            } else {
+                line.push_str(&format!("{number} {item}"));
+            }
+            if idx < items.len() {
+                let used = number_width + 2 + item.chars().count();
+                line.push_str(&" ".repeat(cell_width - used));
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.push_str(prompt);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn runs_down_columns_then_across() {
+        let menu = layout(&items(&["a", "b", "c", "d", "e"]), "#? ", 16, false);
+        assert_eq!(menu, "1) a  4) d\n2) b  5) e\n3) c\n#? ");
+    }
+
+    #[test]
+    fn prompt_alone_without_items() {
+        assert_eq!(layout(&[], "pick: ", 80, false), "pick: ");
+    }
+
+    #[test]
+    fn bold_numbers_keep_the_columns() {
+        let menu = layout(&items(&["one", "two"]), "#? ", 80, true);
+        assert_eq!(menu, "\x1b[1m1)\x1b[0m one  \x1b[1m2)\x1b[0m two\n#? ");
+    }
+}
@@ -0,0 +1,618 @@
+//! Scrolling pager for text longer than the screen
+//!
+//! [`Pager`] shows text a screen at a time on the alternate screen. Space,
+//! `f` and PageDown go forward a page and `b` and PageUp back, `j`/`k` and
+//! the arrow keys move a line, `d`/`u` half a page, `g` and `G` jump to
+//! either end and Left/Right scroll sideways through long lines. `/` and
+//! `?` search forwards and backwards for a regular expression, `n` and `N`
+//! repeat the search, and `q` or Ctrl-C quits. With [`PagerOptions::raw`]
+//! ANSI color sequences pass through to the terminal; otherwise control
+//! characters are shown as `^[` and the like.
+//!
+//! `less` and `more` page files through it, and builtins with long output
+//! check [`should_page`] before handing their text to [`page`].
+
+use crate::input_handler::KeyEvent;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+    QueueableCommand,
+};
+use regex::{Regex, RegexBuilder};
+use std::io::{self, IsTerminal, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Columns a tab advances to a multiple of
+const TAB_WIDTH: usize = 8;
+/// Columns taken by a line number and the space after it
+const NUMBER_WIDTH: usize = 8;
+
+/// How the pager shows text
+#[derive(Debug, Clone, Default)]
+pub struct PagerOptions {
+    /// Pass ANSI escape sequences through instead of showing `^[`
+    pub raw: bool,
+    /// Number the lines
+    pub line_numbers: bool,
+    /// Search without regard to case unless the pattern has capitals
+    pub ignore_case: bool,
+    /// Quit when paging forward at the end, like `more`
+    pub quit_at_eof: bool,
+    /// Name shown in the status line
+    pub name: Option<String>,
+}
+
+/// What the pager did with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagerStep {
+    Continue,
+    Quit,
+}
+
+#[derive(Debug, Clone)]
+struct Search {
+    regex: Regex,
+    backward: bool,
+}
+
+/// Part of a line: an escape sequence passed through, or one character as
+/// it is shown
+#[derive(Debug, PartialEq)]
+enum Piece<'a> {
+    Code(&'a str),
+    Text(String),
+}
+
+/// State of the pager over one text
+#[derive(Debug)]
+pub struct Pager {
+    lines: Vec<String>,
+    options: PagerOptions,
+    /// First line on the screen
+    top: usize,
+    /// First column on the screen
+    left: usize,
+    search: Option<Search>,
+    /// Line of the last match, where `n` carries on from
+    matched: Option<usize>,
+    /// A pattern being typed after `/` (false) or `?` (true)
+    input: Option<(bool, String)>,
+    /// Shown in the status line until the next key
+    message: Option<String>,
+}
+
+impl Pager {
+    pub fn new(text: &str, options: PagerOptions) -> Self {
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+            options,
+            top: 0,
+            left: 0,
+            search: None,
+            matched: None,
+            input: None,
+            message: None,
+        }
+    }
+
+    /// Index of the first line on the screen
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Page the text on the terminal, drawing through `out`, until the
+    /// user quits. The terminal is restored whatever happens.
+    pub fn run<W: Write + ?Sized>(&mut self, out: &mut W) -> io::Result<()> {
+        struct RawModeGuard;
+        impl Drop for RawModeGuard {
+            fn drop(&mut self) {
+                let _ = terminal::disable_raw_mode();
+            }
+        }
+
+        terminal::enable_raw_mode()?;
+        let _guard = RawModeGuard;
+        out.queue(terminal::EnterAlternateScreen)?
+            .queue(cursor::Hide)?;
+        let result = self.event_loop(out);
+        out.queue(cursor::Show)?
+            .queue(terminal::LeaveAlternateScreen)?;
+        out.flush()?;
+        result
+    }
+
+    fn event_loop<W: Write + ?Sized>(&mut self, out: &mut W) -> io::Result<()> {
+        loop {
+            let (width, height) = terminal::size()?;
+            let (width, height) = (usize::from(width), usize::from(height));
+            self.draw(out, width, height)?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release
+                    && self.handle_key(key.into(), width, height) == PagerStep::Quit
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn draw<W: Write + ?Sized>(&self, out: &mut W, width: usize, height: usize) -> io::Result<()> {
+        for (row, line) in self.render(width, height).iter().enumerate() {
+            out.queue(cursor::MoveTo(0, row as u16))?
+                .queue(terminal::Clear(ClearType::CurrentLine))?
+                .queue(Print(line))?;
+        }
+        out.flush()
+    }
+
+    /// The screen `width` columns by `height` rows: the visible lines, `~`
+    /// past the end, and the status line
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let rows = body_rows(height);
+        let mut screen: Vec<String> = (self.top..self.top + rows)
+            .map(|index| match self.lines.get(index) {
+                Some(_) => self.render_line(index, width),
+                None => "~".to_string(),
+            })
+            .collect();
+        screen.push(self.status(rows, width));
+        screen
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, width: usize, height: usize) -> PagerStep {
+        let rows = body_rows(height);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        self.message = None;
+
+        if let Some((backward, mut text)) = self.input.take() {
+            match key.code {
+                KeyCode::Enter => self.start_search(backward, &text, rows),
+                KeyCode::Esc => {}
+                KeyCode::Char('c' | 'g') if ctrl => {}
+                KeyCode::Backspace => {
+                    // Backspace on an empty pattern leaves the prompt
+                    if text.pop().is_some() {
+                        self.input = Some((backward, text));
+                    }
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    text.push(c);
+                    self.input = Some((backward, text));
+                }
+                _ => self.input = Some((backward, text)),
+            }
+            return PagerStep::Continue;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if ctrl => return PagerStep::Quit,
+            KeyCode::Char('n' | 'e') if ctrl => self.scroll_down(1, rows),
+            KeyCode::Char('p' | 'y') if ctrl => self.scroll_up(1),
+            KeyCode::Char('f' | 'v') if ctrl => return self.page_forward(rows),
+            KeyCode::Char('b') if ctrl => self.scroll_up(rows),
+            KeyCode::Char('d') if ctrl => self.scroll_down(half(rows), rows),
+            KeyCode::Char('u') if ctrl => self.scroll_up(half(rows)),
+            KeyCode::Char(_) if ctrl => {}
+            KeyCode::Char('q' | 'Q') => return PagerStep::Quit,
+            KeyCode::Char('j' | 'e') | KeyCode::Down | KeyCode::Enter => self.scroll_down(1, rows),
+            KeyCode::Char('k' | 'y') | KeyCode::Up => self.scroll_up(1),
+            KeyCode::Char(' ' | 'f') | KeyCode::PageDown => return self.page_forward(rows),
+            KeyCode::Char('b') | KeyCode::PageUp => self.scroll_up(rows),
+            KeyCode::Char('d') => self.scroll_down(half(rows), rows),
+            KeyCode::Char('u') => self.scroll_up(half(rows)),
+            KeyCode::Char('g' | '<') | KeyCode::Home => self.top = 0,
+            KeyCode::Char('G' | '>') | KeyCode::End => self.top = self.last_top(rows),
+            KeyCode::Left => self.left = self.left.saturating_sub(half(width)),
+            KeyCode::Right => self.left += half(width),
+            KeyCode::Char('/') => self.input = Some((false, String::new())),
+            KeyCode::Char('?') => self.input = Some((true, String::new())),
+            KeyCode::Char('n') => self.find(false, rows),
+            KeyCode::Char('N') => self.find(true, rows),
+            _ => {}
+        }
+        PagerStep::Continue
+    }
+
+    /// The top line that puts the last line at the bottom of the screen
+    fn last_top(&self, rows: usize) -> usize {
+        self.lines.len().saturating_sub(rows)
+    }
+
+    fn at_end(&self, rows: usize) -> bool {
+        self.top >= self.last_top(rows)
+    }
+
+    fn scroll_down(&mut self, by: usize, rows: usize) {
+        self.top = (self.top + by).min(self.last_top(rows));
+    }
+
+    fn scroll_up(&mut self, by: usize) {
+        self.top = self.top.saturating_sub(by);
+    }
+
+    fn page_forward(&mut self, rows: usize) -> PagerStep {
+        if self.options.quit_at_eof && self.at_end(rows) {
+            return PagerStep::Quit;
+        }
+        self.scroll_down(rows, rows);
+        PagerStep::Continue
+    }
+
+    /// Compile a typed pattern and jump to its first match. An empty
+    /// pattern repeats the last search in the new direction, as in less.
+    fn start_search(&mut self, backward: bool, text: &str, rows: usize) {
+        if text.is_empty() {
+            match &mut self.search {
+                Some(search) => search.backward = backward,
+                None => {
+                    self.message = Some("No previous pattern".to_string());
+                    return;
+                }
+            }
+        } else {
+            let ignore_case = self.options.ignore_case && !text.chars().any(char::is_uppercase);
+            match RegexBuilder::new(text)
+                .case_insensitive(ignore_case)
+                .build()
+            {
+                Ok(regex) => self.search = Some(Search { regex, backward }),
+                Err(_) => {
+                    self.message = Some(format!("Invalid pattern: {text}"));
+                    return;
+                }
+            }
+            self.matched = None;
+        }
+        self.find(false, rows);
+    }
+
+    /// Move to the next line matching the search, in its direction or,
+    /// with `reverse`, the other way. The search carries on from the last
+    /// match while it is on the screen and starts from the top line
+    /// otherwise.
+    fn find(&mut self, reverse: bool, rows: usize) {
+        let Some(search) = &self.search else {
+            self.message = Some("No previous pattern".to_string());
+            return;
+        };
+        let matches = |index: &usize| {
+            let pieces = pieces(&self.lines[*index], self.options.raw);
+            search.regex.is_match(&plain_text(&pieces))
+        };
+        let from = self
+            .matched
+            .filter(|&line| line >= self.top && line < self.top + rows);
+        let found = if search.backward != reverse {
+            (0..from.unwrap_or(self.top)).rev().find(matches)
+        } else {
+            (from.map_or(self.top, |line| line + 1)..self.lines.len()).find(matches)
+        };
+        match found {
+            Some(line) => {
+                self.matched = Some(line);
+                self.top = line.min(self.last_top(rows));
+            }
+            None => self.message = Some("Pattern not found".to_string()),
+        }
+    }
+
+    /// One line cut to the screen from the current column, with matches of
+    /// the search in reverse video
+    fn render_line(&self, index: usize, width: usize) -> String {
+        let mut out = String::new();
+        let mut width = width;
+        if self.options.line_numbers {
+            out.push_str(&format!("{:>1$} ", index + 1, NUMBER_WIDTH - 1));
+            width = width.saturating_sub(NUMBER_WIDTH);
+        }
+        let pieces = pieces(&self.lines[index], self.options.raw);
+        let marks = self.marks(&pieces);
+        let on = SetAttribute(Attribute::Reverse).to_string();
+        let off = SetAttribute(Attribute::NoReverse).to_string();
+        let mut column = 0;
+        let mut marked = false;
+        for (piece, &mark) in pieces.iter().zip(&marks) {
+            let text = match piece {
+                // Codes scrolled off to the left still set the colors
+                Piece::Code(code) => {
+                    out.push_str(code);
+                    continue;
+                }
+                Piece::Text(text) => text,
+            };
+            let start = column;
+            column += match text.as_str() {
+                "\t" => TAB_WIDTH - column % TAB_WIDTH,
+                text => text.chars().map(|c| c.width().unwrap_or(0)).sum(),
+            };
+            if start < self.left {
+                continue;
+            }
+            if column - self.left > width {
+                break;
+            }
+            if mark != marked {
+                out.push_str(if mark { &on } else { &off });
+                marked = mark;
+            }
+            match text.as_str() {
+                "\t" => out.push_str(&" ".repeat(column - start)),
+                text => out.push_str(text),
+            }
+        }
+        if marked {
+            out.push_str(&off);
+        }
+        if self.options.raw {
+            // Keep colors left open by the line from leaking into the next
+            out.push_str(&SetAttribute(Attribute::Reset).to_string());
+        }
+        out
+    }
+
+    /// Which pieces fall inside a match of the search
+    fn marks(&self, pieces: &[Piece]) -> Vec<bool> {
+        let mut marks = vec![false; pieces.len()];
+        let Some(search) = &self.search else {
+            return marks;
+        };
+        let mut starts = Vec::with_capacity(pieces.len());
+        let mut offset = 0;
+        for piece in pieces {
+            starts.push(offset);
+            if let Piece::Text(text) = piece {
+                offset += text.len();
+            }
+        }
+        for found in search.regex.find_iter(&plain_text(pieces)) {
+            for (i, piece) in pieces.iter().enumerate() {
+                if let Piece::Text(text) = piece {
+                    if starts[i] < found.end() && starts[i] + text.len() > found.start() {
+                        marks[i] = true;
+                    }
+                }
+            }
+        }
+        marks
+    }
+
+    /// The bottom line: the pattern being typed, a message, or where the
+    /// screen is in the text, in reverse video
+    fn status(&self, rows: usize, width: usize) -> String {
+        if let Some((backward, text)) = &self.input {
+            return format!("{}{text}", if *backward { '?' } else { '/' });
+        }
+        let last = (self.top + rows).min(self.lines.len());
+        let percent = match self.lines.len() {
+            0 => 100,
+            total => last * 100 / total,
+        };
+        let text = match &self.message {
+            Some(message) => message.clone(),
+            None if self.options.quit_at_eof => format!("--More--({percent}%)"),
+            None if self.at_end(rows) => "(END)".to_string(),
+            None => {
+                let name = match &self.options.name {
+                    Some(name) => format!("{name} "),
+                    None => String::new(),
+                };
+                format!(
+                    "{name}lines {}-{last}/{} {percent}%",
+                    self.top + 1,
+                    self.lines.len()
+                )
+            }
+        };
+        let text: String = text.chars().take(width.saturating_sub(1)).collect();
+        format!(
+            "{}{text}{}",
+            SetAttribute(Attribute::Reverse),
+            SetAttribute(Attribute::NoReverse)
+        )
+    }
+}
+
+/// Rows of text above the status line on a screen `height` rows high
+fn body_rows(height: usize) -> usize {
+    height.saturating_sub(1).max(1)
+}
+
+fn half(size: usize) -> usize {
+    (size / 2).max(1)
+}
+
+/// Split a line into escape sequences, kept whole when `raw`, and
+/// characters as shown, with control characters in caret notation
+fn pieces(line: &str, raw: bool) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if raw && c == '\x1b' {
+            let len = escape_len(rest);
+            pieces.push(Piece::Code(&rest[..len]));
+            rest = &rest[len..];
+            continue;
+        }
+        pieces.push(Piece::Text(match c {
+            '\t' => "\t".to_string(),
+            '\x7f' => "^?".to_string(),
+            c if c < ' ' => format!("^{}", char::from(c as u8 + b'@')),
+            c => c.to_string(),
+        }));
+        rest = &rest[c.len_utf8()..];
+    }
+    pieces
+}
+
+/// Length of the escape sequence starting `text`: a CSI sequence up to its
+/// final byte, an OSC sequence up to BEL or `ESC \`, or ESC and the
+/// character after it
+fn escape_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    match bytes.get(1) {
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map_or(text.len(), |at| at + 3),
+        Some(b']') => {
+            let body = &text[2..];
+            let end = body
+                .char_indices()
+                .find_map(|(at, c)| match c {
+                    '\x07' => Some(at + 1),
+                    '\x1b' if body[at + 1..].starts_with('\\') => Some(at + 2),
+                    _ => None,
+                })
+                .unwrap_or(body.len());
+            2 + end
+        }
+        Some(_) => 1 + text[1..].chars().next().map_or(0, char::len_utf8),
+        None => 1,
+    }
+}
+
+/// The characters of a line as shown, which searches match against
+fn plain_text(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Text(text) => Some(text.as_str()),
+            Piece::Code(_) => None,
+        })
+        .collect()
+}
+
+/// Whether `text` should go through the pager rather than straight to
+/// stdout: stdout is a terminal and the text has more lines than fit
+/// above the status line
+pub fn should_page(text: &str) -> bool {
+    if !io::stdout().is_terminal() {
+        return false;
+    }
+    match terminal::size() {
+        Ok((_, rows)) => text.lines().count() >= usize::from(rows),
+        Err(_) => false,
+    }
+}
+
+/// Page `text` on the terminal, drawing through `out`
+pub fn page<W: Write + ?Sized>(text: &str, options: PagerOptions, out: &mut W) -> io::Result<()> {
+    Pager::new(text, options).run(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|i| format!("line {i}\n")).collect()
+    }
+
+    fn type_keys(pager: &mut Pager, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            pager.handle_key(key(code), 40, 6);
+        }
+    }
+
+    #[test]
+    fn scrolls_within_the_text() {
+        let mut pager = Pager::new(&numbered(12), PagerOptions::default());
+        // Five rows of text above the status line
+        assert_eq!(pager.render(40, 6)[0], "line 1");
+        assert_eq!(
+            pager.handle_key(key(KeyCode::Char(' ')), 40, 6),
+            PagerStep::Continue
+        );
+        assert_eq!(pager.top(), 5);
+        type_keys(&mut pager, "  ");
+        assert_eq!(pager.top(), 7);
+        assert!(pager.render(40, 6)[5].contains("(END)"));
+        type_keys(&mut pager, "kk");
+        assert_eq!(pager.top(), 5);
+        type_keys(&mut pager, "g");
+        assert_eq!(pager.top(), 0);
+        assert_eq!(
+            pager.handle_key(key(KeyCode::Char('q')), 40, 6),
+            PagerStep::Quit
+        );
+
+        let short = Pager::new("only\n", PagerOptions::default());
+        assert_eq!(
+            short.render(40, 4),
+            ["only", "~", "~", "\x1b[7m(END)\x1b[27m"]
+        );
+    }
+
+    #[test]
+    fn more_quits_past_the_end() {
+        let options = PagerOptions {
+            quit_at_eof: true,
+            ..Default::default()
+        };
+        let mut pager = Pager::new(&numbered(8), options);
+        assert!(pager.render(40, 6)[5].contains("--More--(62%)"));
+        assert_eq!(
+            pager.handle_key(key(KeyCode::Char(' ')), 40, 6),
+            PagerStep::Continue
+        );
+        assert_eq!(
+            pager.handle_key(key(KeyCode::Char(' ')), 40, 6),
+            PagerStep::Quit
+        );
+    }
+
+    #[test]
+    fn searches_both_ways_and_highlights_matches() {
+        let mut pager = Pager::new(&numbered(30), PagerOptions::default());
+        type_keys(&mut pager, "/line 2\n");
+        assert_eq!(pager.top(), 1);
+        assert_eq!(pager.render(40, 6)[0], "\x1b[7mline 2\x1b[27m");
+        type_keys(&mut pager, "n");
+        assert_eq!(pager.top(), 19);
+        type_keys(&mut pager, "n");
+        assert_eq!(pager.top(), 20);
+        type_keys(&mut pager, "N");
+        assert_eq!(pager.top(), 19);
+
+        type_keys(&mut pager, "?line 1$\n");
+        assert_eq!(pager.top(), 0);
+        type_keys(&mut pager, "/nowhere\n");
+        assert_eq!(pager.top(), 0);
+        assert!(pager.render(40, 6)[5].contains("Pattern not found"));
+    }
+
+    #[test]
+    fn shows_or_passes_escape_sequences() {
+        let text = "\x1b[31mred\x1b[0m\tend\x01";
+        let plain = Pager::new(text, PagerOptions::default());
+        assert_eq!(plain.render_line(0, 80), "^[[31mred^[[0m  end^A");
+
+        let raw = Pager::new(
+            text,
+            PagerOptions {
+                raw: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            raw.render_line(0, 80),
+            "\x1b[31mred\x1b[0m     end^A\x1b[0m"
+        );
+        // Cut to the width, keeping the color codes
+        assert_eq!(raw.render_line(0, 2), "\x1b[31mre\x1b[0m");
+        assert_eq!(escape_len("\x1b]8;;url\x07rest"), 9);
+    }
+}
@@ -1,15 +1,30 @@
 //! Simple prompt display system for NexusShell CUI
 //!
 //! This module provides PS1-based prompt functionality with optional Git information
-//! and system status display, designed for CUI mode efficiency.
-
-use anyhow::Result;
+//! and system status display, designed for CUI mode efficiency, and the
+//! template engine behind the interactive prompt: `{user}@{host} {cwd:short}
+//! {git_branch} {exit_code} {duration}` with a right-side prompt and segment
+//! styles taken from the theme. `{plugin:NAME}` shows the segment NAME a
+//! plugin registered.
+
+use crate::{config::UiConfig, themes::NexusTheme};
+use anyhow::{bail, Result};
 use crossterm::{
-    style::{Color, ResetColor, SetForegroundColor},
+    style::{Color, ContentStyle, ResetColor, SetForegroundColor, Stylize},
     ExecutableCommand,
 };
 use hostname;
-use std::{env, io::stdout, path::Path, process::Command};
+use nxsh_core::PluginCommandRegistry;
+use std::{
+    collections::HashMap,
+    env,
+    io::stdout,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 use whoami;
 
 /// Prompt configuration for CUI mode  
@@ -748,6 +763,564 @@ impl PromptFormatter {
     }
 }
 
+/// Left prompt used when no template is configured
+pub const DEFAULT_PROMPT_TEMPLATE: &str =
+    "{user}@{host} {cwd:short} {git_branch}{git_status} {exit_code} {duration} {jobs}\n{symbol} ";
+
+/// Right prompt used when no template is configured
+pub const DEFAULT_RIGHT_PROMPT_TEMPLATE: &str = "{time}";
+
+/// Prompt accepted lines are redrawn with when no template is configured
+pub const DEFAULT_TRANSIENT_PROMPT_TEMPLATE: &str = "{symbol} ";
+
+/// Segments a template may name
+const SEGMENTS: &[&str] = &[
+    "user",
+    "host",
+    "cwd",
+    "git_branch",
+    "git_status",
+    "exit_code",
+    "duration",
+    "jobs",
+    "time",
+    "symbol",
+    "shell",
+    "plugin",
+];
+
+/// Segments that run external commands or plugins, evaluated off-thread
+/// under a timeout
+const SLOW_SEGMENTS: &[&str] = &["git_branch", "git_status", "plugin"];
+
+/// Built-in style of each segment, used when the theme has no
+/// `prompt.<segment>` entry
+fn default_segment_style(name: &str) -> ContentStyle {
+    let style = ContentStyle::new();
+    match name {
+        "user" => style.with(Color::Green).bold(),
+        "host" => style.with(Color::Blue),
+        "cwd" => style.with(Color::Cyan).bold(),
+        "git_branch" => style.with(Color::Magenta),
+        "git_status" | "exit_code" | "symbol_error" => style.with(Color::Red).bold(),
+        "duration" => style.with(Color::Yellow),
+        "jobs" => style.with(Color::Blue),
+        "time" => style.with(Color::DarkGrey),
+        "symbol" => style.with(Color::Green).bold(),
+        _ => style,
+    }
+}
+
+/// What the prompt describes: the outcome of the last command and the
+/// shell around it
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    /// Exit code of the last command
+    pub exit_code: i32,
+    /// How long the last command ran
+    pub duration: Option<Duration>,
+    /// Running and stopped background jobs
+    pub jobs: usize,
+    /// Working directory; the process's own when unset
+    pub cwd: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Segment { name: String, arg: Option<String> },
+}
+
+/// A parsed prompt template: literal text with `{segment}` or
+/// `{segment:arg}` placeholders, where `{{` and `}}` stand for braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pieces: Vec<Piece>,
+}
+
+impl PromptTemplate {
+    /// Parse `template`, rejecting unknown segments and unbalanced braces
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => bail!("unclosed `{{` in prompt template"),
+                        }
+                    }
+                    let (name, arg) = match spec.split_once(':') {
+                        Some((name, arg)) => (name.trim(), Some(arg.to_string())),
+                        None => (spec.trim(), None),
+                    };
+                    // `{git}` is what older configs call the branch
+                    let name = if name == "git" { "git_branch" } else { name };
+                    if !SEGMENTS.contains(&name) {
+                        bail!("unknown prompt segment `{name}`");
+                    }
+                    if name == "plugin" && arg.as_deref().is_none_or(str::is_empty) {
+                        bail!("prompt segment `plugin` needs a name, as `{{plugin:NAME}}`");
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Segment {
+                        name: name.to_string(),
+                        arg,
+                    });
+                }
+                '}' => bail!("unmatched `}}` in prompt template"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self { pieces })
+    }
+
+    fn slow_segments(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Segment { name, arg } if SLOW_SEGMENTS.contains(&name.as_str()) => {
+                Some((name.as_str(), arg.as_deref()))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// A rendered prompt; any part may be empty
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderedPrompt {
+    pub left: String,
+    pub right: String,
+    /// Compact prompt the accepted line is redrawn with
+    pub transient: String,
+}
+
+type SegmentKey = (String, Option<String>);
+
+/// Renders left and right prompt templates, styling each segment from the
+/// theme. Segments that shell out or run plugins run in parallel and are
+/// given `timeout`, or the timeout of the plugin segment, to finish; one
+/// that misses it shows its last value for the directory until it catches up.
+#[derive(Debug, Clone)]
+pub struct PromptEngine {
+    left: PromptTemplate,
+    right: Option<PromptTemplate>,
+    transient: Option<PromptTemplate>,
+    styles: HashMap<String, ContentStyle>,
+    timeout: Duration,
+    color: bool,
+    cache: Arc<Mutex<HashMap<(SegmentKey, PathBuf), String>>>,
+    /// Segments registered by plugins
+    plugins: PluginCommandRegistry,
+}
+
+impl Default for PromptEngine {
+    fn default() -> Self {
+        Self::new(
+            PromptTemplate::parse(DEFAULT_PROMPT_TEMPLATE).expect("default prompt template"),
+            Some(
+                PromptTemplate::parse(DEFAULT_RIGHT_PROMPT_TEMPLATE)
+                    .expect("default right prompt template"),
+            ),
+        )
+    }
+}
+
+impl PromptEngine {
+    pub fn new(left: PromptTemplate, right: Option<PromptTemplate>) -> Self {
+        Self {
+            left,
+            right,
+            transient: None,
+            styles: HashMap::new(),
+            timeout: Duration::from_millis(150),
+            color: true,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            plugins: PluginCommandRegistry::default(),
+        }
+    }
+
+    /// Parse both templates; an empty right template means no right prompt
+    pub fn from_templates(left: &str, right: &str) -> Result<Self> {
+        let right = if right.is_empty() {
+            None
+        } else {
+            Some(PromptTemplate::parse(right)?)
+        };
+        Ok(Self::new(PromptTemplate::parse(left)?, right))
+    }
+
+    /// Engine for the templates and timeout in `config`
+    pub fn from_ui_config(config: &UiConfig) -> Result<Self> {
+        let mut engine =
+            Self::from_templates(&config.prompt_template, &config.right_prompt_template)?
+                .with_timeout(Duration::from_millis(config.prompt_segment_timeout_ms));
+        if !config.transient_prompt_template.is_empty() {
+            engine =
+                engine.with_transient(PromptTemplate::parse(&config.transient_prompt_template)?);
+        }
+        Ok(engine)
+    }
+
+    /// Also render a compact prompt for lines once they are accepted
+    pub fn with_transient(mut self, transient: PromptTemplate) -> Self {
+        self.transient = Some(transient);
+        self
+    }
+
+    /// Style segments with the theme's `prompt.<segment>` entries and
+    /// literal text with its `prompt` entry
+    pub fn with_theme(mut self, theme: &NexusTheme) -> Self {
+        for (name, style) in &theme.styles {
+            if name == "prompt" || name.starts_with("prompt.") {
+                self.styles.insert(name.clone(), style.clone().into());
+            }
+        }
+        self
+    }
+
+    /// How long slow segments may take before a cached value is used
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Render without ANSI styling
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Show the segments plugins register in `plugins` as `{plugin:NAME}`
+    pub fn with_plugins(mut self, plugins: PluginCommandRegistry) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    pub fn render(&self, ctx: &PromptContext) -> RenderedPrompt {
+        let cwd = ctx
+            .cwd
+            .clone()
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_default();
+        let slow = self.evaluate_slow(&cwd);
+        let render = |template: &Option<PromptTemplate>| {
+            template
+                .as_ref()
+                .map(|template| self.render_template(template, ctx, &cwd, &slow))
+                .unwrap_or_default()
+        };
+        RenderedPrompt {
+            left: self.render_template(&self.left, ctx, &cwd, &slow),
+            right: render(&self.right),
+            transient: render(&self.transient),
+        }
+    }
+
+    /// Run every slow segment on its own thread and collect what finishes
+    /// before the deadline; late results still land in the cache
+    fn evaluate_slow(&self, cwd: &Path) -> HashMap<SegmentKey, String> {
+        let mut wanted: Vec<SegmentKey> = Vec::new();
+        let optional = self.right.iter().chain(self.transient.iter());
+        for (name, arg) in self
+            .left
+            .slow_segments()
+            .chain(optional.flat_map(|t| t.slow_segments()))
+        {
+            let key = (name.to_string(), arg.map(str::to_string));
+            if !wanted.contains(&key) {
+                wanted.push(key);
+            }
+        }
+        let mut values = HashMap::new();
+        if wanted.is_empty() {
+            return values;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let start = Instant::now();
+        let mut deadlines = HashMap::new();
+        for key in &wanted {
+            let plugin = match key {
+                (name, Some(arg)) if name == "plugin" => self.plugins.segment(arg),
+                _ => None,
+            };
+            let timeout = plugin
+                .as_ref()
+                .and_then(|segment| segment.timeout)
+                .unwrap_or(self.timeout);
+            deadlines.insert(key.clone(), start + timeout);
+            let tx = tx.clone();
+            let key = key.clone();
+            let cwd = cwd.to_path_buf();
+            let cache = Arc::clone(&self.cache);
+            thread::spawn(move || {
+                let value = match plugin {
+                    Some(segment) => (segment.render)(&cwd),
+                    None => slow_segment(&key.0, key.1.as_deref(), &cwd),
+                };
+                if let Ok(mut cache) = cache.lock() {
+                    cache.insert((key.clone(), cwd), value.clone());
+                }
+                let _ = tx.send((key, value));
+            });
+        }
+        drop(tx);
+
+        while values.len() < wanted.len() {
+            // Wait as long as the most patient segment still running
+            let deadline = deadlines
+                .iter()
+                .filter(|(key, _)| !values.contains_key(*key))
+                .map(|(_, deadline)| *deadline)
+                .max()
+                .unwrap_or(start);
+            let left = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(left) {
+                Ok((key, value)) => {
+                    values.insert(key, value);
+                }
+                Err(_) => break,
+            }
+        }
+        if values.len() < wanted.len() {
+            if let Ok(cache) = self.cache.lock() {
+                for key in wanted {
+                    if !values.contains_key(&key) {
+                        let cached = cache.get(&(key.clone(), cwd.to_path_buf())).cloned();
+                        values.insert(key, cached.unwrap_or_default());
+                    }
+                }
+            }
+        }
+        values
+    }
+
+    fn render_template(
+        &self,
+        template: &PromptTemplate,
+        ctx: &PromptContext,
+        cwd: &Path,
+        slow: &HashMap<SegmentKey, String>,
+    ) -> String {
+        let mut out = String::new();
+        // Spaces between segments are only drawn when there is something on
+        // both sides, so empty optional segments leave no gaps
+        let mut pending: Option<&str> = None;
+        let last = template.pieces.len().saturating_sub(1);
+        for (i, piece) in template.pieces.iter().enumerate() {
+            match piece {
+                Piece::Text(text) if !out.is_empty() && text.chars().all(|c| c == ' ') => {
+                    pending.get_or_insert(text.as_str());
+                    if i == last {
+                        out.push_str(&self.paint("prompt", pending.take().unwrap_or_default()));
+                    }
+                }
+                Piece::Text(text) => {
+                    if let Some(space) = pending.take() {
+                        if !text.starts_with('\n') {
+                            out.push_str(&self.paint("prompt", space));
+                        }
+                    }
+                    out.push_str(&self.paint("prompt", text));
+                }
+                Piece::Segment { name, arg } => {
+                    let value = if SLOW_SEGMENTS.contains(&name.as_str()) {
+                        slow.get(&(name.clone(), arg.clone()))
+                            .cloned()
+                            .unwrap_or_default()
+                    } else {
+                        segment(name, arg.as_deref(), ctx, cwd)
+                    };
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let Some(space) = pending.take() {
+                        out.push_str(&self.paint("prompt", space));
+                    }
+                    let style = if name == "symbol" && ctx.exit_code != 0 {
+                        "symbol_error"
+                    } else {
+                        name.as_str()
+                    };
+                    out.push_str(&self.paint(style, &value));
+                }
+            }
+        }
+        out
+    }
+
+    fn paint(&self, segment: &str, text: &str) -> String {
+        if !self.color || text.is_empty() {
+            return text.to_string();
+        }
+        let style = if segment == "prompt" {
+            match self.styles.get("prompt") {
+                Some(style) => *style,
+                None => return text.to_string(),
+            }
+        } else {
+            self.styles
+                .get(&format!("prompt.{segment}"))
+                .copied()
+                .unwrap_or_else(|| default_segment_style(segment))
+        };
+        // Style each line apart so a multi-line prompt resets before the
+        // line break the editor draws at
+        text.split('\n')
+            .map(|line| {
+                if line.is_empty() {
+                    String::new()
+                } else {
+                    style.apply(line).to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Value of a segment that needs no external command
+fn segment(name: &str, arg: Option<&str>, ctx: &PromptContext, cwd: &Path) -> String {
+    match name {
+        "user" => whoami::username(),
+        "host" => {
+            let host = hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_default();
+            match arg {
+                Some("full") => host,
+                _ => host.split('.').next().unwrap_or_default().to_string(),
+            }
+        }
+        "cwd" => format_cwd(cwd, arg),
+        "exit_code" if ctx.exit_code != 0 => format!("✘ {}", ctx.exit_code),
+        "duration" => {
+            // Only commands at least this many seconds long are shown
+            let threshold = arg
+                .and_then(|a| a.trim_end_matches('s').parse::<f64>().ok())
+                .unwrap_or(2.0);
+            match ctx.duration {
+                Some(took) if took.as_secs_f64() >= threshold => format_duration(took),
+                _ => String::new(),
+            }
+        }
+        "jobs" if ctx.jobs > 0 => format!("⚙ {}", ctx.jobs),
+        "time" => {
+            use std::fmt::Write as _;
+            // A bad format fails the write instead of panicking in `to_string`
+            let mut time = String::new();
+            let _ = write!(
+                time,
+                "{}",
+                chrono::Local::now().format(arg.unwrap_or("%H:%M:%S"))
+            );
+            time
+        }
+        "symbol" => arg.unwrap_or("❯").to_string(),
+        "shell" => "nxsh".to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Value of a segment that runs git
+fn slow_segment(name: &str, arg: Option<&str>, cwd: &Path) -> String {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(cwd)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    match name {
+        "git_branch" => match git(&["rev-parse", "--abbrev-ref", "HEAD"]) {
+            Some(branch) if branch == "HEAD" => {
+                git(&["rev-parse", "--short", "HEAD"]).unwrap_or_default()
+            }
+            Some(branch) => branch,
+            None => String::new(),
+        },
+        "git_status" => match git(&["status", "--porcelain"]) {
+            Some(status) if !status.is_empty() => arg.unwrap_or("*").to_string(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Working directory with the home directory as `~`; `short` abbreviates
+/// every parent to its first letter and `base` keeps only the last part
+pub fn format_cwd(cwd: &Path, arg: Option<&str>) -> String {
+    let full = match dirs::home_dir() {
+        Some(home) if cwd.starts_with(&home) => match cwd.strip_prefix(&home) {
+            Ok(rel) if rel.as_os_str().is_empty() => "~".to_string(),
+            Ok(rel) => format!("~/{}", rel.display()),
+            Err(_) => cwd.display().to_string(),
+        },
+        _ => cwd.display().to_string(),
+    };
+    match arg {
+        Some("base") => match cwd.file_name() {
+            Some(base) if full != "~" => base.to_string_lossy().into_owned(),
+            _ => full,
+        },
+        Some("short") => {
+            let parts: Vec<&str> = full.split('/').collect();
+            let last = parts.len().saturating_sub(1);
+            parts
+                .iter()
+                .enumerate()
+                .map(|(i, part)| {
+                    if i == last || part.is_empty() || *part == "~" {
+                        part.to_string()
+                    } else {
+                        // Keep a leading dot so hidden directories stay recognisable
+                        let keep = if part.starts_with('.') { 2 } else { 1 };
+                        part.chars().take(keep).collect()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/")
+        }
+        _ => full,
+    }
+}
+
+/// `850ms`, `4.2s`, `3m 12s` or `1h 5m`
+pub(crate) fn format_duration(took: Duration) -> String {
+    let secs = took.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", took.as_secs_f64())
+    } else {
+        format!("{}ms", took.as_millis())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -771,4 +1344,109 @@ mod tests {
         assert!(formatter.config.show_hostname);
         assert!(!formatter.config.git_simplified);
     }
+
+    #[test]
+    fn template_parses_segments_and_escapes() {
+        let template = PromptTemplate::parse("{{{user}}} {git} {cwd:short}").unwrap();
+        assert_eq!(
+            template.pieces,
+            vec![
+                Piece::Text("{".into()),
+                Piece::Segment {
+                    name: "user".into(),
+                    arg: None
+                },
+                Piece::Text("} ".into()),
+                Piece::Segment {
+                    name: "git_branch".into(),
+                    arg: None
+                },
+                Piece::Text(" ".into()),
+                Piece::Segment {
+                    name: "cwd".into(),
+                    arg: Some("short".into())
+                },
+            ]
+        );
+        assert!(PromptTemplate::parse("{nope}").is_err());
+        assert!(PromptTemplate::parse("{user").is_err());
+    }
+
+    #[test]
+    fn empty_segments_leave_no_gaps() {
+        let engine =
+            PromptEngine::from_templates("{shell} {exit_code} {duration} {jobs}\n{symbol} ", "")
+                .unwrap()
+                .with_color(false);
+        let ok = PromptContext::default();
+        assert_eq!(engine.render(&ok).left, "nxsh\n❯ ");
+
+        let failed = PromptContext {
+            exit_code: 2,
+            duration: Some(Duration::from_millis(3500)),
+            jobs: 1,
+            cwd: None,
+        };
+        let rendered = engine.render(&failed);
+        assert_eq!(rendered.left, "nxsh ✘ 2 3.5s ⚙ 1\n❯ ");
+        assert_eq!(rendered.right, "");
+    }
+
+    #[test]
+    fn duration_threshold_and_format() {
+        let engine = PromptEngine::from_templates("{duration:10}", "")
+            .unwrap()
+            .with_color(false);
+        let ctx = PromptContext {
+            duration: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        assert_eq!(engine.render(&ctx).left, "");
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h 5m");
+    }
+
+    #[test]
+    fn cwd_short_abbreviates_parents() {
+        let path = Path::new("/usr/local/.config/nexus");
+        assert_eq!(format_cwd(path, Some("short")), "/u/l/.c/nexus");
+        assert_eq!(format_cwd(path, Some("base")), "nexus");
+        assert_eq!(format_cwd(path, None), "/usr/local/.config/nexus");
+    }
+
+    #[test]
+    fn plugin_segments_render_within_their_timeout() {
+        let plugins = PluginCommandRegistry::default();
+        plugins.register_segment(nxsh_core::PromptSegment {
+            name: "kube".to_string(),
+            plugin: "kube@1.0.0".to_string(),
+            timeout: None,
+            render: Arc::new(|_: &Path| "prod".to_string()),
+        });
+        plugins.register_segment(nxsh_core::PromptSegment {
+            name: "slow".to_string(),
+            plugin: "kube@1.0.0".to_string(),
+            timeout: Some(Duration::from_millis(10)),
+            render: Arc::new(|_: &Path| {
+                thread::sleep(Duration::from_millis(300));
+                "late".to_string()
+            }),
+        });
+        let engine =
+            PromptEngine::from_templates("{plugin:kube} {plugin:slow} {plugin:gone} {symbol} ", "")
+                .unwrap()
+                .with_color(false)
+                .with_timeout(Duration::from_secs(5))
+                .with_plugins(plugins.clone());
+        let ctx = PromptContext::default();
+        assert_eq!(engine.render(&ctx).left, "prod ❯ ");
+        // The late value is shown once it is in
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(engine.render(&ctx).left, "prod late ❯ ");
+        plugins.unregister_plugin("kube@1.0.0");
+        assert_eq!(engine.render(&ctx).left, "❯ ");
+
+        assert!(PromptTemplate::parse("{plugin}").is_err());
+    }
 }
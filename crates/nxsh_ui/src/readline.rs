@@ -1,9 +1,14 @@
 //! Enhanced ReadLine implementation for NexusShell CUI
 //! Provides rich line editing with tab completion, history, and syntax highlighting
 
+use crate::abbreviations::{self, Abbreviations};
 use crate::completion::{CompletionResult, NexusCompleter};
-use crate::history::History;
-use crate::prompt::PromptRenderer;
+use crate::config::UiConfig;
+use crate::editing::EditState;
+use crate::history::{History, HistoryConfig};
+use crate::history_search::{HistorySearch, SearchStep};
+use crate::input_handler::{self, EditMode, InputAction, InputConfig, InputHandler, ViMotion};
+use crate::prompt::{format_duration, PromptRenderer};
 use crossterm::{
     cursor,
     event::{self, Event, KeyCode, KeyEvent as CrosstermKeyEvent, KeyEventKind, KeyModifiers},
@@ -11,8 +16,11 @@ use crossterm::{
     terminal::{self, disable_raw_mode, enable_raw_mode},
     ExecutableCommand, QueueableCommand,
 };
+use nxsh_core::{CommandReport, PostCommandHook};
 use std::io::{self, stdout, Stdout, Write};
-use unicode_width::UnicodeWidthStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Key event wrapper
 #[derive(Debug, Clone)]
@@ -40,6 +48,8 @@ pub struct ReadLineConfig {
     pub completion_max_items: usize,
     pub auto_completion: bool,
     pub vi_mode: bool,
+    /// Show a dimmed suggestion from history after the cursor while typing
+    pub autosuggestions: bool,
 }
 
 impl Default for ReadLineConfig {
@@ -52,6 +62,7 @@ impl Default for ReadLineConfig {
             completion_max_items: 50,
             auto_completion: false,
             vi_mode: false,
+            autosuggestions: true,
         }
     }
 }
@@ -86,7 +97,31 @@ pub struct ReadLine {
 
     // History navigation
     history_index: Option<usize>,
-    history_search: Option<String>,
+
+    // Rest of the suggested line, drawn dimmed after the cursor
+    suggestion: Option<String>,
+
+    // Keymap and the line editing it drives
+    input: InputHandler,
+    editing: EditState,
+
+    // Ctrl-R fuzzy search overlay, while open
+    search: Option<HistorySearch>,
+
+    // Expanded in place on Space and Enter
+    abbreviations: &'static RwLock<Abbreviations>,
+
+    // Drawn right-aligned on the input row while it fits
+    right_prompt: String,
+    // Replaces the prompt of an accepted line; empty keeps the full prompt
+    transient_prompt: String,
+    // When the last line was accepted, and how the command it ran ended as
+    // reported by the executor's post-command hook or `record_exit_code`
+    accepted_at: Option<Instant>,
+    last_report: Arc<Mutex<Option<CommandReport>>>,
+    // Print the exit status and run time of failed or slow commands
+    show_command_status: bool,
+    command_status_threshold: Duration,
 }
 
 impl ReadLine {
@@ -96,11 +131,20 @@ impl ReadLine {
 
     pub fn with_config(config: ReadLineConfig) -> io::Result<Self> {
         let (width, _) = terminal::size()?;
+        let ui = UiConfig::default();
+        let history = History::with_config(HistoryConfig {
+            persist_to_file: config.enable_history,
+            ..HistoryConfig::from_ui_config(&ui)
+        });
+        let mut input = InputHandler::with_config(InputConfig::from_ui_config(&ui));
+        if config.vi_mode {
+            input.set_edit_mode(EditMode::Vi);
+        }
 
         Ok(Self {
             config,
             completion_engine: NexusCompleter::new(),
-            history: History::new(),
+            history,
             prompt_renderer: PromptRenderer::default(),
             line: String::new(),
             cursor_pos: 0,
@@ -114,10 +158,92 @@ impl ReadLine {
             completion_index: None,
             completion_prefix: String::new(),
             history_index: None,
-            history_search: None,
+            suggestion: None,
+            input,
+            editing: EditState::new(),
+            search: None,
+            abbreviations: abbreviations::shared(),
+            right_prompt: String::new(),
+            transient_prompt: String::new(),
+            accepted_at: None,
+            last_report: Arc::new(Mutex::new(None)),
+            show_command_status: ui.show_command_status,
+            command_status_threshold: Duration::from_millis(ui.command_status_threshold_ms),
+        })
+    }
+
+    /// Switch between the Vi and Emacs keymaps, as `set -o vi|emacs` does
+    pub fn set_vi_mode(&mut self, vi: bool) {
+        self.config.vi_mode = vi;
+        self.input
+            .set_edit_mode(if vi { EditMode::Vi } else { EditMode::Emacs });
+    }
+
+    /// Completion engine, for registering what commands accept
+    pub fn completer_mut(&mut self) -> &mut NexusCompleter {
+        &mut self.completion_engine
+    }
+
+    /// Command history, including commands other sessions have saved
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Hook for the executor that tells this editor how each command ended
+    /// and how long it ran
+    pub fn post_command_hook(&self) -> PostCommandHook {
+        let last_report = Arc::clone(&self.last_report);
+        Arc::new(move |report: &CommandReport| {
+            if let Ok(mut last) = last_report.lock() {
+                *last = Some(report.clone());
+            }
         })
     }
 
+    /// Record the exit code of the line last returned by `read_line`, which
+    /// saves it to the history file. Unless the executor already reported
+    /// the command, it is timed from when the line was accepted. A failed or
+    /// slow command has its status printed.
+    pub fn record_exit_code(&mut self, exit_code: i32) {
+        let accepted_at = self.accepted_at.take();
+        let report = {
+            let mut last = self.last_report.lock().unwrap_or_else(|e| e.into_inner());
+            let report = last.get_or_insert_with(|| CommandReport {
+                exit_code,
+                duration: accepted_at.map(|at| at.elapsed()).unwrap_or_default(),
+            });
+            report.exit_code = exit_code;
+            report.clone()
+        };
+        if self.config.enable_history {
+            self.history.record_exit_code(exit_code);
+        }
+        if self.show_command_status
+            && accepted_at.is_some()
+            && (exit_code != 0 || report.duration >= self.command_status_threshold)
+        {
+            eprintln!("{}", command_status_line(&report));
+        }
+    }
+
+    /// Exit code and run time of the last command
+    pub fn last_status(&self) -> Option<(i32, Duration)> {
+        let last = self.last_report.lock().ok()?;
+        last.as_ref().map(|r| (r.exit_code, r.duration))
+    }
+
+    /// Prompt the next accepted line is redrawn with, so multi-line prompts
+    /// collapse to one line in the scrollback; empty to keep them
+    pub fn set_transient_prompt(&mut self, transient_prompt: &str) {
+        self.transient_prompt = transient_prompt.to_string();
+    }
+
+    /// Text shown at the right edge of the input row by the next
+    /// `read_line`; empty for none
+    pub fn set_right_prompt(&mut self, right_prompt: &str) {
+        self.right_prompt = right_prompt.to_string();
+    }
+
     /// Read a line of input with full editing capabilities
     pub fn read_line(&mut self, prompt: &str) -> io::Result<String> {
         self.prompt = prompt.to_string();
@@ -131,6 +257,10 @@ impl ReadLine {
         // Ensure no stale panel height from previous sessions
         self.last_panel_height = 0;
         self.history_index = None;
+        self.suggestion = None;
+        self.search = None;
+        self.input.start_line();
+        self.editing.reset();
 
         enable_raw_mode()?;
 
@@ -147,6 +277,13 @@ impl ReadLine {
                     let key_event = KeyEvent::from(key);
 
                     if let Some(result) = self.handle_key(key_event)? {
+                        // Leave the accepted line on screen without its suggestion
+                        let had_suggestion = self.suggestion.take().is_some();
+                        if !self.transient_prompt.is_empty() {
+                            self.collapse_prompt()?;
+                        } else if had_suggestion {
+                            self.refresh_display()?;
+                        }
                         disable_raw_mode()?;
                         stdout().execute(Print("\n"))?;
 
@@ -154,13 +291,19 @@ impl ReadLine {
                             self.history.add_entry(result.clone());
                         }
 
+                        self.accepted_at = Some(Instant::now());
+                        if let Ok(mut last) = self.last_report.lock() {
+                            *last = None;
+                        }
                         return Ok(result);
                     }
 
+                    self.update_suggestion();
                     self.refresh_display()?;
                 }
                 Event::Resize(width, _) => {
                     self.screen_width = width;
+                    self.update_suggestion();
                     self.refresh_display()?;
                 }
                 _ => {}
@@ -169,8 +312,30 @@ impl ReadLine {
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> io::Result<Option<String>> {
-        match key.code {
-            KeyCode::Enter => {
+        let key = input_handler::KeyEvent {
+            code: key.code,
+            modifiers: key.modifiers,
+        };
+        if let Some(search) = &mut self.search {
+            match search.handle_key(key, &self.history) {
+                SearchStep::Continue => {}
+                SearchStep::Accept(command) => {
+                    self.line = command;
+                    self.cursor_pos = self.line.len();
+                    self.search = None;
+                }
+                SearchStep::Cancel => self.search = None,
+            }
+            return Ok(None);
+        }
+
+        let Some(action) = self.input.handle_key(key) else {
+            return Ok(None);
+        };
+        let panel_open = self.completion_index.is_some() && !self.completions.is_empty();
+
+        match action {
+            InputAction::Submit => {
                 // If completion panel is open, Enter accepts the current selection
                 if let Some(idx) = self.completion_index {
                     if let Some(comp) = self.completions.get(idx).cloned() {
@@ -184,156 +349,169 @@ impl ReadLine {
                         return Ok(None);
                     }
                 }
+                // An expansion that places the cursor is left to be filled in
+                if self.expand_abbreviation() == Some(true) {
+                    return Ok(None);
+                }
                 return Ok(Some(self.line.clone()));
             }
 
-            KeyCode::Esc => {
+            InputAction::Cancel => {
                 self.clear_completion_state();
             }
 
-            KeyCode::Tab => {
+            InputAction::Complete | InputAction::CompleteNext => {
                 if self.config.enable_completion {
                     self.handle_tab_completion()?;
                 }
             }
 
-            KeyCode::BackTab => {
+            InputAction::CompletePrevious => {
                 if self.config.enable_completion && self.completion_index.is_some() {
                     self.previous_completion();
                 }
             }
 
-            KeyCode::Backspace => {
-                if self.cursor_pos > 0 {
-                    // UTF-8 safe backspace: remove the previous char boundary
-                    let prev = self.line[..self.cursor_pos]
-                        .char_indices()
-                        .last()
-                        .map(|(i, _)| i)
-                        .unwrap_or(0);
-                    self.line.drain(prev..self.cursor_pos);
-                    self.cursor_pos = prev;
-                    self.clear_completion_state();
-                }
+            InputAction::MoveLeft if panel_open => self.move_completion_left(),
+            InputAction::MoveRight if panel_open => self.move_completion_right(),
+            InputAction::MoveRight if self.cursor_pos >= self.line.len() => {
+                self.accept_suggestion(false);
             }
-
-            KeyCode::Delete => {
-                if self.cursor_pos < self.line.len() {
-                    // UTF-8 safe delete: remove next char boundary
-                    let mut it = self.line[self.cursor_pos..].char_indices();
-                    // Skip current (0) and take next boundary
-                    let next = it
-                        .nth(0)
-                        .map(|(_, ch)| self.cursor_pos + ch.len_utf8())
-                        .unwrap_or(self.line.len());
-                    self.line.drain(self.cursor_pos..next);
-                    self.clear_completion_state();
-                }
-            }
-
-            KeyCode::Left => {
-                if self.completion_index.is_some() && !self.completions.is_empty() {
-                    self.move_completion_left();
-                } else if self.cursor_pos > 0 {
-                    // Move left by one Unicode scalar
-                    let prev = self.line[..self.cursor_pos]
-                        .char_indices()
-                        .last()
-                        .map(|(i, _)| i)
-                        .unwrap_or(0);
-                    self.cursor_pos = prev;
-                    self.clear_completion_state();
-                }
-            }
-
-            KeyCode::Right => {
-                if self.completion_index.is_some() && !self.completions.is_empty() {
-                    self.move_completion_right();
-                } else if self.cursor_pos < self.line.len() {
-                    // Move right by one Unicode scalar
-                    let mut it = self.line[self.cursor_pos..].char_indices();
-                    let next = it
-                        .nth(0)
-                        .map(|(i, ch)| self.cursor_pos + i + ch.len_utf8())
-                        .unwrap_or(self.line.len());
-                    self.cursor_pos = next;
-                    self.clear_completion_state();
-                }
+            InputAction::MoveToEnd
+            | InputAction::ViMove {
+                motion: ViMotion::LineEnd,
+                ..
+            } if self.suggestion.is_some() => {
+                self.accept_suggestion(false);
             }
+            InputAction::AcceptSuggestionWord => self.accept_suggestion(true),
 
-            KeyCode::Up => {
-                if self.completion_index.is_some() && !self.completions.is_empty() {
+            InputAction::HistoryPrevious => {
+                if panel_open {
                     self.previous_completion();
                 } else if self.config.enable_history {
                     self.history_previous();
                 }
             }
 
-            KeyCode::Down => {
-                if self.completion_index.is_some() && !self.completions.is_empty() {
+            InputAction::HistoryNext => {
+                if panel_open {
                     self.next_completion();
                 } else if self.config.enable_history {
                     self.history_next();
                 }
             }
 
-            KeyCode::Home => {
-                self.cursor_pos = 0;
-                self.clear_completion_state();
+            InputAction::HistorySearch => {
+                if self.config.enable_history {
+                    self.clear_completion_state();
+                    self.search = Some(HistorySearch::new(&self.line, &self.history));
+                }
             }
 
-            KeyCode::End => {
-                self.cursor_pos = self.line.len();
+            InputAction::Interrupt => return Ok(Some(String::new())),
+            InputAction::EndOfInput if self.line.is_empty() => return Ok(Some(String::new())),
+            InputAction::EndOfInput => {
+                self.editing
+                    .apply(&InputAction::Delete, &mut self.line, &mut self.cursor_pos);
+            }
+
+            InputAction::ClearScreen => {
+                stdout().execute(terminal::Clear(terminal::ClearType::All))?;
+                stdout().execute(cursor::MoveTo(0, 0))?;
+            }
+
+            InputAction::InsertChar(' ') => {
+                // An expansion that places the cursor takes the space
+                if self.expand_abbreviation() != Some(true) {
+                    self.editing.apply(
+                        &InputAction::InsertChar(' '),
+                        &mut self.line,
+                        &mut self.cursor_pos,
+                    );
+                }
                 self.clear_completion_state();
             }
 
-            KeyCode::Char(c) => {
-                if key.modifiers.contains(KeyModifiers::CONTROL) {
-                    match c {
-                        'c' => {
-                            return Ok(Some(String::new()));
-                        }
-                        'd' => {
-                            if self.line.is_empty() {
-                                return Ok(Some(String::new()));
-                            }
-                        }
-                        'a' => {
-                            self.cursor_pos = 0;
-                        }
-                        'e' => {
-                            self.cursor_pos = self.line.len();
-                        }
-                        'k' => {
-                            self.line.truncate(self.cursor_pos);
-                        }
-                        'u' => {
-                            self.line.drain(0..self.cursor_pos);
-                            self.cursor_pos = 0;
-                        }
-                        'w' => {
-                            self.delete_word_backward();
-                        }
-                        'l' => {
-                            stdout().execute(terminal::Clear(terminal::ClearType::All))?;
-                            stdout().execute(cursor::MoveTo(0, 0))?;
-                        }
-                        _ => {}
-                    }
-                } else {
-                    // Insert character at cursor (UTF-8 safe)
-                    self.line.insert(self.cursor_pos, c);
-                    self.cursor_pos += c.len_utf8();
+            action => {
+                if self
+                    .editing
+                    .apply(&action, &mut self.line, &mut self.cursor_pos)
+                {
                     self.clear_completion_state();
                 }
             }
-
-            _ => {}
         }
 
         Ok(None)
     }
 
+    /// Expand the abbreviation ending at the cursor. `Some(true)` when the
+    /// expansion placed the cursor itself.
+    fn expand_abbreviation(&mut self) -> Option<bool> {
+        let expansion = self
+            .abbreviations
+            .read()
+            .ok()?
+            .expand(&self.line, self.cursor_pos)?;
+        self.line = expansion.line;
+        self.cursor_pos = expansion.cursor;
+        Some(expansion.positioned)
+    }
+
+    /// Work out the suggestion shown after the cursor: the latest history
+    /// entry that extends the line, or else the first completion of the word
+    /// being typed. Only offered with the cursor at the end of the line.
+    fn update_suggestion(&mut self) {
+        self.suggestion = None;
+        if !self.config.autosuggestions
+            || self.search.is_some()
+            || self.line.trim().is_empty()
+            || self.cursor_pos != self.line.len()
+            || !self.completions.is_empty()
+        {
+            return;
+        }
+        if self.config.enable_history {
+            if let Some(command) = self.history.suggest(&self.line) {
+                self.suggestion = Some(command[self.line.len()..].to_string());
+                return;
+            }
+        }
+        if self.config.enable_completion && !self.line.ends_with(char::is_whitespace) {
+            let word = self.get_completion_prefix();
+            self.suggestion = self
+                .completion_engine
+                .complete(&self.line, self.cursor_pos)
+                .into_iter()
+                .find_map(|c| {
+                    c.completion
+                        .strip_prefix(word.as_str())
+                        .filter(|rest| !rest.is_empty())
+                        .map(str::to_string)
+                });
+        }
+    }
+
+    /// Append the suggestion to the line, or with `next_word` only up to the
+    /// end of its next word
+    fn accept_suggestion(&mut self, next_word: bool) {
+        let Some(suggestion) = self.suggestion.take() else {
+            return;
+        };
+        let end = if next_word {
+            let start = suggestion.len() - suggestion.trim_start().len();
+            suggestion[start..]
+                .find(char::is_whitespace)
+                .map_or(suggestion.len(), |i| start + i)
+        } else {
+            suggestion.len()
+        };
+        self.line.push_str(&suggestion[..end]);
+        self.cursor_pos = self.line.len();
+        self.clear_completion_state();
+    }
+
     fn handle_tab_completion(&mut self) -> io::Result<()> {
         if self.completions.is_empty() {
             // Start new completion
@@ -524,46 +702,6 @@ impl ReadLine {
         }
     }
 
-    fn history_search_backward(&mut self) -> io::Result<()> {
-        // Simple implementation - could be enhanced with incremental search
-        if let Some(entry) = self.history.previous() {
-            self.line = entry;
-            self.cursor_pos = self.line.len();
-        }
-        Ok(())
-    }
-
-    fn delete_word_backward(&mut self) {
-        let mut end = self.cursor_pos;
-
-        // Skip whitespace
-        while end > 0
-            && self
-                .line
-                .chars()
-                .nth(end - 1)
-                .unwrap_or(' ')
-                .is_whitespace()
-        {
-            end -= 1;
-        }
-
-        // Delete word
-        while end > 0
-            && !self
-                .line
-                .chars()
-                .nth(end - 1)
-                .unwrap_or(' ')
-                .is_whitespace()
-        {
-            end -= 1;
-        }
-
-        self.line.drain(end..self.cursor_pos);
-        self.cursor_pos = end;
-    }
-
     fn display_prompt(&mut self) -> io::Result<()> {
         let mut out = stdout();
         // Capture current row as the prompt start
@@ -578,6 +716,18 @@ impl ReadLine {
             // Clamp starting row so that the bottom of the prompt aligns to the last screen row
             self.input_row = max_row.saturating_sub(prompt_rows.saturating_sub(1));
         }
+        // Scroll the screen up if the search overlay does not fit below the line
+        if let Some(search) = &self.search {
+            let caret_row = self.input_row + prompt_rows - 1;
+            let wanted = search.height(self.screen_width as usize) as u16;
+            let scroll = wanted
+                .saturating_sub(max_row.saturating_sub(caret_row))
+                .min(self.input_row);
+            if scroll > 0 {
+                out.queue(terminal::ScrollUp(scroll))?;
+                self.input_row -= scroll;
+            }
+        }
 
         // Proactively clear the prompt area (all lines the prompt will occupy)
         for r in 0..self.prompt_lines as u16 {
@@ -598,10 +748,55 @@ impl ReadLine {
             out.queue(cursor::MoveTo(0, row))?;
             out.queue(Print(line))?;
         }
+        let caret_row = (self.input_row + prompt_rows - 1).min(max_row);
+        self.draw_right_prompt(&mut out, caret_row)?;
         out.flush()?;
         Ok(())
     }
 
+    // Redraw the accepted line after the transient prompt, clearing the rows
+    // the full prompt and any panel took
+    fn collapse_prompt(&mut self) -> io::Result<()> {
+        let mut out = stdout();
+        out.queue(cursor::MoveTo(0, self.input_row))?;
+        out.queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
+        self.prompt = self.transient_prompt.clone();
+        let (rows, last_row_col) = self.compute_prompt_metrics();
+        self.prompt_lines = rows.max(1);
+        self.prompt_width = last_row_col;
+        self.clear_completion_state();
+        self.last_panel_height = 0;
+        let right_prompt = std::mem::take(&mut self.right_prompt);
+        let drawn = self.refresh_display();
+        self.right_prompt = right_prompt;
+        drawn
+    }
+
+    // Right prompt at the end of the caret row, left out when the line and
+    // its suggestion would run into it
+    fn draw_right_prompt(&self, out: &mut Stdout, row: u16) -> io::Result<()> {
+        if self.right_prompt.is_empty() {
+            return Ok(());
+        }
+        let width = Self::visible_width(&self.right_prompt);
+        let suggested = self
+            .suggestion
+            .as_deref()
+            .and_then(|s| s.lines().next())
+            .map(UnicodeWidthStr::width)
+            .unwrap_or(0);
+        let used = self.prompt_width + UnicodeWidthStr::width(self.line.as_str()) + suggested;
+        let screen = self.screen_width as usize;
+        // Keep a blank column on either side, as other shells do
+        if used + width + 2 > screen {
+            return Ok(());
+        }
+        out.queue(cursor::MoveTo((screen - width - 1) as u16, row))?;
+        out.queue(Print(&self.right_prompt))?;
+        out.queue(ResetColor)?;
+        Ok(())
+    }
+
     // Compute display width ignoring ANSI escape sequences
     fn visible_width(s: &str) -> usize {
         UnicodeWidthStr::width(Self::strip_ansi(s).as_str())
@@ -722,6 +917,27 @@ impl ReadLine {
             out.queue(Print(&self.line))?;
         }
 
+        // Dimmed suggestion after the typed text, cut to fit the screen
+        if let Some(suggestion) = &self.suggestion {
+            let used = self.prompt_width + UnicodeWidthStr::width(self.line.as_str());
+            let room = (self.screen_width as usize).saturating_sub(used + 1);
+            let mut width = 0;
+            let shown: String = suggestion
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .take_while(|&c| {
+                    width += UnicodeWidthChar::width(c).unwrap_or(0);
+                    width <= room
+                })
+                .collect();
+            out.queue(SetForegroundColor(Color::DarkGrey))?;
+            out.queue(Print(shown))?;
+            out.queue(ResetColor)?;
+        }
+        self.draw_right_prompt(&mut out, caret_row)?;
+
         // Position cursor using display width (Unicode aware)
         let line_left = &self.line[..self.cursor_pos];
         let line_left_width = UnicodeWidthStr::width(line_left);
@@ -731,8 +947,24 @@ impl ReadLine {
         }
         out.queue(cursor::MoveTo(desired_col, caret_row))?;
 
-        // Show completions if active; otherwise clear any previously drawn panel
-        if !self.completions.is_empty() {
+        // Show the search overlay or completions if active; otherwise clear
+        // any previously drawn panel
+        if let Some(search) = &mut self.search {
+            let top = caret_row.saturating_add(1);
+            let rows = max_row.saturating_sub(caret_row) as usize;
+            let height = search.draw(&mut out, top, self.screen_width as usize, rows)?;
+            for r in height..self.last_panel_height.min(rows) {
+                out.queue(cursor::MoveTo(0, top + r as u16))?;
+                out.queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+            }
+            self.last_panel_height = height;
+            if height > 0 {
+                let col = search
+                    .caret_column()
+                    .min((self.screen_width as usize).saturating_sub(1));
+                out.queue(cursor::MoveTo(col as u16, top))?;
+            }
+        } else if !self.completions.is_empty() {
             // Flush so cursor position is accurate before drawing the panel
             out.flush()?;
             let current_row = caret_row;
@@ -958,6 +1190,149 @@ impl NexusCompleter {
     }
 }
 
+/// Options for [`read_raw`], the terminal side of the `read` builtin
+#[derive(Debug, Clone)]
+pub struct RawReadOptions {
+    /// Character that ends the input (Enter always ends it for `'\n'`)
+    pub delimiter: char,
+    /// Stop after this many characters
+    pub max_chars: Option<usize>,
+    /// Echo typed characters (false for `read -s`)
+    pub echo: bool,
+    /// Give up after this long
+    pub timeout: Option<std::time::Duration>,
+}
+
+impl Default for RawReadOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: '\n',
+            max_chars: None,
+            echo: true,
+            timeout: None,
+        }
+    }
+}
+
+/// How a [`read_raw`] call ended; every variant carries what was typed so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawReadOutcome {
+    /// Delimiter seen or character limit reached
+    Complete(String),
+    /// Timeout expired first
+    TimedOut(String),
+    /// Ctrl-D on an empty line
+    Eof(String),
+    /// Ctrl-C
+    Interrupted(String),
+}
+
+/// Read one record from the terminal without line editing, for `read`.
+///
+/// Puts the terminal in raw mode only for the duration of the call, so it
+/// composes with [`ReadLine`], which has already restored cooked mode by
+/// the time a command runs. The prompt goes to stderr like bash.
+pub fn read_raw(prompt: &str, options: &RawReadOptions) -> io::Result<RawReadOutcome> {
+    struct RawModeGuard;
+    impl Drop for RawModeGuard {
+        fn drop(&mut self) {
+            let _ = disable_raw_mode();
+        }
+    }
+
+    let mut err = io::stderr();
+    if !prompt.is_empty() {
+        err.write_all(prompt.as_bytes())?;
+        err.flush()?;
+    }
+    enable_raw_mode()?;
+    let _guard = RawModeGuard;
+
+    let deadline = options.timeout.map(|t| std::time::Instant::now() + t);
+    let mut input = String::new();
+    let finish = |err: &mut io::Stderr, outcome: RawReadOutcome| -> io::Result<RawReadOutcome> {
+        if options.echo || !matches!(outcome, RawReadOutcome::Complete(_)) {
+            err.write_all(b"\r\n")?;
+            err.flush()?;
+        }
+        Ok(outcome)
+    };
+
+    loop {
+        if let Some(deadline) = deadline {
+            let now = std::time::Instant::now();
+            if now >= deadline || !event::poll(deadline - now)? {
+                return finish(&mut err, RawReadOutcome::TimedOut(input));
+            }
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return finish(&mut err, RawReadOutcome::Interrupted(input));
+            }
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if input.is_empty() {
+                    return finish(&mut err, RawReadOutcome::Eof(input));
+                }
+            }
+            KeyCode::Enter if options.delimiter == '\n' => {
+                return finish(&mut err, RawReadOutcome::Complete(input));
+            }
+            KeyCode::Backspace => {
+                if input.pop().is_some() && options.echo {
+                    err.write_all(b"\x08 \x08")?;
+                    err.flush()?;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(_) | KeyCode::Tab => {
+                let ch = match key.code {
+                    KeyCode::Char(c) => c,
+                    KeyCode::Tab => '\t',
+                    _ => '\n',
+                };
+                if ch == options.delimiter {
+                    return finish(&mut err, RawReadOutcome::Complete(input));
+                }
+                input.push(ch);
+                if options.echo {
+                    let shown = if ch == '\n' {
+                        "\r\n".to_string()
+                    } else {
+                        ch.to_string()
+                    };
+                    err.write_all(shown.as_bytes())?;
+                    err.flush()?;
+                }
+                if options
+                    .max_chars
+                    .is_some_and(|max| input.chars().count() >= max)
+                {
+                    return finish(&mut err, RawReadOutcome::Complete(input));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `✘ exit 1 · took 3.2s`, dimmed, for a command that failed or ran long
+fn command_status_line(report: &CommandReport) -> String {
+    let status = if report.exit_code == 0 {
+        "✔".to_string()
+    } else {
+        format!("✘ exit {}", report.exit_code)
+    };
+    format!(
+        "\x1b[2m{status} · took {}\x1b[0m",
+        format_duration(report.duration)
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -971,10 +1346,15 @@ mod tests {
             completion_max_items: 5,
             auto_completion: false,
             vi_mode: false,
+            autosuggestions: true,
         })
         .expect("rl")
     }
 
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
     #[test]
     fn utf8_left_right_moves_by_char() {
         let mut rl = mk();
@@ -1021,4 +1401,147 @@ mod tests {
         // First char removed (multibyte)
         assert_eq!(rl.line, "c");
     }
+
+    #[test]
+    fn autosuggestion_from_history_is_accepted_whole_or_by_word() {
+        let mut rl = mk();
+        rl.config.enable_history = true;
+        rl.history = History::with_config(HistoryConfig {
+            persist_to_file: false,
+            ..Default::default()
+        });
+        rl.history.add_entry("git commit -m wip".to_string());
+
+        rl.line = "git c".to_string();
+        rl.cursor_pos = rl.line.len();
+        rl.update_suggestion();
+        assert_eq!(rl.suggestion.as_deref(), Some("ommit -m wip"));
+
+        let _ = rl.handle_key(key(KeyCode::Right, KeyModifiers::ALT));
+        assert_eq!(rl.line, "git commit");
+        rl.update_suggestion();
+        let _ = rl.handle_key(key(KeyCode::Right, KeyModifiers::ALT));
+        assert_eq!(rl.line, "git commit -m");
+        rl.update_suggestion();
+        let _ = rl.handle_key(key(KeyCode::End, KeyModifiers::empty()));
+        assert_eq!(rl.line, "git commit -m wip");
+        assert_eq!(rl.cursor_pos, rl.line.len());
+
+        // Nothing is suggested with the cursor inside the line
+        rl.line = "git c".to_string();
+        rl.cursor_pos = 1;
+        rl.update_suggestion();
+        assert_eq!(rl.suggestion, None);
+    }
+
+    #[test]
+    fn vi_mode_edits_through_the_keymap() {
+        let mut rl = mk();
+        rl.set_vi_mode(true);
+        rl.input.start_line();
+        for c in "echo foo bar".chars() {
+            let _ = rl.handle_key(key(KeyCode::Char(c), KeyModifiers::empty()));
+        }
+        let _ = rl.handle_key(key(KeyCode::Esc, KeyModifiers::empty()));
+        for c in "bdb".chars() {
+            let _ = rl.handle_key(key(KeyCode::Char(c), KeyModifiers::empty()));
+        }
+        assert_eq!(rl.line, "echo bar");
+        let _ = rl.handle_key(key(KeyCode::Char('P'), KeyModifiers::SHIFT));
+        assert_eq!(rl.line, "echo foo bar");
+
+        rl.set_vi_mode(false);
+        let _ = rl.handle_key(key(KeyCode::Char('a'), KeyModifiers::CONTROL));
+        assert_eq!(rl.cursor_pos, 0);
+    }
+
+    #[test]
+    fn ctrl_r_search_puts_the_chosen_command_on_the_line() {
+        let mut rl = mk();
+        rl.config.enable_history = true;
+        rl.history = History::with_config(HistoryConfig {
+            persist_to_file: false,
+            ..Default::default()
+        });
+        rl.history.add_entry("cargo build --release".to_string());
+        rl.history.add_entry("ls".to_string());
+
+        let _ = rl.handle_key(key(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert!(rl.search.is_some());
+        for c in "cbr".chars() {
+            let _ = rl.handle_key(key(KeyCode::Char(c), KeyModifiers::empty()));
+        }
+        assert_eq!(rl.line, "");
+        let _ = rl.handle_key(key(KeyCode::Enter, KeyModifiers::empty()));
+        assert!(rl.search.is_none());
+        assert_eq!(rl.line, "cargo build --release");
+
+        // Esc leaves the line as it was
+        let _ = rl.handle_key(key(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        let _ = rl.handle_key(key(KeyCode::Char('l'), KeyModifiers::empty()));
+        let _ = rl.handle_key(key(KeyCode::Esc, KeyModifiers::empty()));
+        assert_eq!(rl.line, "cargo build --release");
+    }
+
+    #[test]
+    fn record_exit_code_times_the_accepted_line() {
+        let mut rl = mk();
+        assert_eq!(rl.last_status(), None);
+        rl.accepted_at = Some(Instant::now() - Duration::from_secs(3));
+        rl.record_exit_code(7);
+        let (code, took) = rl.last_status().expect("status");
+        assert_eq!(code, 7);
+        assert!(took >= Duration::from_secs(3));
+        assert!(rl.accepted_at.is_none());
+
+        // The executor's own timing wins over the editor's
+        rl.accepted_at = Some(Instant::now() - Duration::from_secs(3));
+        *rl.last_report.lock().unwrap() = None;
+        let hook = rl.post_command_hook();
+        hook(&CommandReport {
+            exit_code: 0,
+            duration: Duration::from_millis(12),
+        });
+        rl.record_exit_code(0);
+        assert_eq!(rl.last_status(), Some((0, Duration::from_millis(12))));
+    }
+
+    #[test]
+    fn abbreviations_expand_on_space_and_enter() {
+        use crate::abbreviations::Abbreviation;
+
+        let mut rl = mk();
+        let mut abbrs = Abbreviations::new();
+        abbrs.add(Abbreviation::new("gs", "git status"));
+        abbrs.add(Abbreviation {
+            cursor_marker: Some("%".into()),
+            ..Abbreviation::new("gc", "git commit -m \"%\"")
+        });
+        rl.abbreviations = Box::leak(Box::new(RwLock::new(abbrs)));
+
+        for c in "gs ".chars() {
+            let _ = rl.handle_key(key(KeyCode::Char(c), KeyModifiers::empty()));
+        }
+        assert_eq!(rl.line, "git status ");
+
+        rl.line.clear();
+        rl.cursor_pos = 0;
+        for c in "gc".chars() {
+            let _ = rl.handle_key(key(KeyCode::Char(c), KeyModifiers::empty()));
+        }
+        // Enter stops inside the quotes instead of running the line
+        let submitted = rl
+            .handle_key(key(KeyCode::Enter, KeyModifiers::empty()))
+            .unwrap();
+        assert_eq!(submitted, None);
+        assert_eq!(rl.line, "git commit -m \"\"");
+        assert_eq!(rl.cursor_pos, 15);
+
+        rl.line = "gs".to_string();
+        rl.cursor_pos = 2;
+        let submitted = rl
+            .handle_key(key(KeyCode::Enter, KeyModifiers::empty()))
+            .unwrap();
+        assert_eq!(submitted.as_deref(), Some("git status"));
+    }
 }
@@ -75,18 +75,10 @@ impl From<SerializableStyle> for ContentStyle {
     fn from(val: SerializableStyle) -> Self {
         let mut style = ContentStyle::new();
         if let Some(fg) = val.foreground {
-            let target_color = if fg.contains("Blue") {
-                Color::Blue
-            } else if fg.contains("Red") {
-                Color::Red
-            } else if fg.contains("Green") {
-                Color::Green
-            } else if fg.contains("Yellow") {
-                Color::Yellow
-            } else {
-                Color::White
-            };
-            style = style.with(target_color);
+            style = style.with(parse_color(&fg).unwrap_or(Color::White));
+        }
+        if let Some(color) = val.background.as_deref().and_then(parse_color) {
+            style = style.on(color);
         }
         if val.bold {
             style = style.bold();
@@ -101,6 +93,56 @@ impl From<SerializableStyle> for ContentStyle {
     }
 }
 
+/// Parse a style colour: a crossterm name such as `DarkGrey` or
+/// `dark_grey`, a `#rrggbb` hex value, or the `Rgb { r: .., g: .., b: .. }`
+/// form styles are saved in
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if value.starts_with('#') {
+        let rgb = RgbColor::from_hex(value)?;
+        return Some(Color::Rgb {
+            r: rgb.r,
+            g: rgb.g,
+            b: rgb.b,
+        });
+    }
+    if let Some(fields) = value.strip_prefix("Rgb") {
+        let channels: Vec<u8> = fields
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|n| n.parse().ok())
+            .collect();
+        if let [r, g, b] = channels[..] {
+            return Some(Color::Rgb { r, g, b });
+        }
+        return None;
+    }
+    let name = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    Some(match name.as_str() {
+        "black" => Color::Black,
+        "darkgrey" | "darkgray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "darkred" => Color::DarkRed,
+        "green" => Color::Green,
+        "darkgreen" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "darkyellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "darkblue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "darkmagenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "darkcyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "reset" => Color::Reset,
+        _ => return None,
+    })
+}
+
 // Extended color scheme with enhanced UI elements
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColorScheme {